@@ -0,0 +1,466 @@
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::{debug, info};
+
+use crate::error::{AutomationError, AutomationResult};
+use crate::trigger::value_to_json;
+use crate::workflow::{ArtifactType, Task, Value};
+
+pub mod store;
+
+pub use store::{LocalObjectStore, ObjectStore};
+
+// Container tasks see their working directory mounted here
+pub const CONTAINER_WORKDIR: &str = "/workspace";
+
+const COMPLETION_MARKER: &str = "_completed";
+const CACHE_MANIFEST: &str = "manifest.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArtifactConfig {
+    pub max_artifact_bytes: u64,
+    pub retention_days: i64,
+}
+
+impl Default for ArtifactConfig {
+    fn default() -> Self {
+        Self {
+            max_artifact_bytes: 100 * 1024 * 1024,
+            retention_days: 7,
+        }
+    }
+}
+
+impl ArtifactConfig {
+    pub fn with_max_artifact_bytes(mut self, bytes: u64) -> Self {
+        self.max_artifact_bytes = bytes;
+        self
+    }
+
+    pub fn with_retention_days(mut self, days: i64) -> Self {
+        self.retention_days = days;
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StoredArtifact {
+    pub name: String,
+    pub task_id: String,
+    pub key: String,
+    pub size_bytes: u64,
+    pub sha256: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheEntry {
+    pub key: String,
+    pub task_id: String,
+    pub outputs: HashMap<String, Value>,
+    pub artifacts: Vec<StoredArtifact>,
+    pub created_at: DateTime<Utc>,
+}
+
+fn run_prefix(run_id: &str) -> String {
+    format!("runs/{}/", run_id)
+}
+
+fn cache_prefix(task_id: &str, cache_key: &str) -> String {
+    format!("cache/{}/{}/", task_id, cache_key)
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+// Maps a declared artifact path onto the host working directory
+pub fn resolve_path(working_dir: &Path, artifact_path: &str) -> AutomationResult<PathBuf> {
+    let path = Path::new(artifact_path);
+    if path.components().any(|c| matches!(c, Component::ParentDir)) {
+        return Err(AutomationError::Validation(format!(
+            "Artifact path '{}' must not contain '..'",
+            artifact_path
+        )));
+    }
+    Ok(match path.strip_prefix(CONTAINER_WORKDIR) {
+        Ok(relative) => working_dir.join(relative),
+        Err(_) if path.is_absolute() => path.to_path_buf(),
+        Err(_) => working_dir.join(path),
+    })
+}
+
+pub struct ArtifactManager {
+    store: Arc<dyn ObjectStore>,
+    config: ArtifactConfig,
+}
+
+impl ArtifactManager {
+    pub fn new(store: Arc<dyn ObjectStore>, config: ArtifactConfig) -> Self {
+        Self { store, config }
+    }
+
+    async fn upload_file(&self, key: String, name: &str, task_id: &str, file: &Path) -> AutomationResult<StoredArtifact> {
+        let metadata = tokio::fs::metadata(file).await.map_err(|e| {
+            AutomationError::NotFound(format!("Artifact {} missing at {}: {}", name, file.display(), e))
+        })?;
+        if metadata.len() > self.config.max_artifact_bytes {
+            return Err(AutomationError::Validation(format!(
+                "Artifact {} is {} bytes, exceeding the {} byte limit",
+                name,
+                metadata.len(),
+                self.config.max_artifact_bytes
+            )));
+        }
+        let data = tokio::fs::read(file)
+            .await
+            .map_err(|e| AutomationError::Internal(format!("Failed to read artifact {}: {}", name, e)))?;
+
+        let artifact = StoredArtifact {
+            name: name.to_string(),
+            task_id: task_id.to_string(),
+            key,
+            size_bytes: data.len() as u64,
+            sha256: sha256_hex(&data),
+        };
+        self.store.put(&artifact.key, data).await?;
+        debug!("Uploaded artifact {} ({} bytes) to {}", name, artifact.size_bytes, artifact.key);
+        Ok(artifact)
+    }
+
+    // Fetches an artifact and writes it to `dest` only if its checksum still matches
+    async fn download_verified(&self, artifact: &StoredArtifact, dest: &Path) -> AutomationResult<Vec<u8>> {
+        let data = self.store.get(&artifact.key).await?;
+        let actual = sha256_hex(&data);
+        if actual != artifact.sha256 {
+            return Err(AutomationError::Internal(format!(
+                "Checksum mismatch for artifact {}: expected {}, got {}",
+                artifact.name, artifact.sha256, actual
+            )));
+        }
+        if let Some(parent) = dest.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|e| AutomationError::Internal(format!("Failed to create {}: {}", parent.display(), e)))?;
+        }
+        tokio::fs::write(dest, &data)
+            .await
+            .map_err(|e| AutomationError::Internal(format!("Failed to write artifact {}: {}", artifact.name, e)))?;
+        Ok(data)
+    }
+
+    // Output and Cache artifacts are both published to the run so downstream tasks can consume them
+    pub async fn upload_outputs(&self, run_id: &str, task: &Task, working_dir: &Path) -> AutomationResult<Vec<StoredArtifact>> {
+        let mut uploaded = Vec::new();
+        for artifact in &task.config.artifacts {
+            if matches!(artifact.type_, ArtifactType::Input) {
+                continue;
+            }
+            let key = format!("{}{}/{}", run_prefix(run_id), task.id, artifact.name);
+            let file = resolve_path(working_dir, &artifact.path)?;
+            uploaded.push(self.upload_file(key, &artifact.name, &task.id, &file).await?);
+        }
+        Ok(uploaded)
+    }
+
+    pub async fn download_inputs(
+        &self,
+        task: &Task,
+        working_dir: &Path,
+        available: &HashMap<String, StoredArtifact>,
+    ) -> AutomationResult<Vec<StoredArtifact>> {
+        let mut downloaded = Vec::new();
+        for artifact in &task.config.artifacts {
+            if !matches!(artifact.type_, ArtifactType::Input) {
+                continue;
+            }
+            let stored = available.get(&artifact.name).ok_or_else(|| {
+                AutomationError::NotFound(format!(
+                    "Task {} requires artifact {} but no upstream task produced it",
+                    task.id, artifact.name
+                ))
+            })?;
+            self.download_verified(stored, &resolve_path(working_dir, &artifact.path)?).await?;
+            downloaded.push(stored.clone());
+        }
+        Ok(downloaded)
+    }
+
+    // Content hash over everything that determines the task's result; None if the task declares no Cache artifacts
+    pub fn cache_key(&self, task: &Task, inputs: &[StoredArtifact]) -> Option<String> {
+        if !task
+            .config
+            .artifacts
+            .iter()
+            .any(|a| matches!(a.type_, ArtifactType::Cache))
+        {
+            return None;
+        }
+        let mut input_artifacts: Vec<(&str, &str)> = inputs.iter().map(|a| (a.name.as_str(), a.sha256.as_str())).collect();
+        input_artifacts.sort();
+        let environment: std::collections::BTreeMap<_, _> = task.config.environment.iter().collect();
+
+        // serde_json maps are ordered, so this serialization is stable across runs
+        let material = serde_json::json!({
+            "task_id": task.id,
+            "task_type": serde_json::to_value(&task.task_type).unwrap_or_default(),
+            "inputs": task
+                .config
+                .inputs
+                .iter()
+                .map(|(k, v)| (k.clone(), value_to_json(v)))
+                .collect::<serde_json::Map<_, _>>(),
+            "environment": environment,
+            "artifacts": input_artifacts,
+        });
+        Some(sha256_hex(material.to_string().as_bytes()))
+    }
+
+    pub async fn lookup_cache(&self, task: &Task, cache_key: &str) -> AutomationResult<Option<CacheEntry>> {
+        let manifest_key = format!("{}{}", cache_prefix(&task.id, cache_key), CACHE_MANIFEST);
+        let data = match self.store.get(&manifest_key).await {
+            Ok(data) => data,
+            Err(AutomationError::NotFound(_)) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let entry: CacheEntry = serde_json::from_slice(&data)
+            .map_err(|e| AutomationError::Internal(format!("Corrupt cache manifest {}: {}", manifest_key, e)))?;
+        for artifact in &entry.artifacts {
+            if !self.store.exists(&artifact.key).await? {
+                return Ok(None);
+            }
+        }
+        Ok(Some(entry))
+    }
+
+    // Restores cached artifacts into the working directory and republishes them under the current run
+    pub async fn restore_cache(
+        &self,
+        run_id: &str,
+        task: &Task,
+        entry: &CacheEntry,
+        working_dir: &Path,
+    ) -> AutomationResult<Vec<StoredArtifact>> {
+        let mut restored = Vec::new();
+        for cached in &entry.artifacts {
+            let declared = task
+                .config
+                .artifacts
+                .iter()
+                .find(|a| a.name == cached.name)
+                .ok_or_else(|| AutomationError::Internal(format!("Cached artifact {} is no longer declared", cached.name)))?;
+            let data = self
+                .download_verified(cached, &resolve_path(working_dir, &declared.path)?)
+                .await?;
+            let artifact = StoredArtifact {
+                key: format!("{}{}/{}", run_prefix(run_id), task.id, cached.name),
+                ..cached.clone()
+            };
+            self.store.put(&artifact.key, data).await?;
+            restored.push(artifact);
+        }
+        info!("Restored {} cached artifacts for task {}", restored.len(), task.id);
+        Ok(restored)
+    }
+
+    pub async fn save_cache(
+        &self,
+        task: &Task,
+        cache_key: &str,
+        outputs: &HashMap<String, Value>,
+        working_dir: &Path,
+    ) -> AutomationResult<CacheEntry> {
+        let prefix = cache_prefix(&task.id, cache_key);
+        let mut artifacts = Vec::new();
+        for artifact in &task.config.artifacts {
+            if !matches!(artifact.type_, ArtifactType::Cache) {
+                continue;
+            }
+            let key = format!("{}{}", prefix, artifact.name);
+            let file = resolve_path(working_dir, &artifact.path)?;
+            artifacts.push(self.upload_file(key, &artifact.name, &task.id, &file).await?);
+        }
+
+        let entry = CacheEntry {
+            key: cache_key.to_string(),
+            task_id: task.id.clone(),
+            outputs: outputs.clone(),
+            artifacts,
+            created_at: Utc::now(),
+        };
+        let manifest = serde_json::to_vec(&entry)
+            .map_err(|e| AutomationError::Internal(format!("Failed to encode cache manifest: {}", e)))?;
+        // The manifest goes last so a partially written entry is never treated as a hit
+        self.store.put(&format!("{}{}", prefix, CACHE_MANIFEST), manifest).await?;
+        Ok(entry)
+    }
+
+    pub async fn complete_run(&self, run_id: &str, completed_at: DateTime<Utc>) -> AutomationResult<()> {
+        let key = format!("{}{}", run_prefix(run_id), COMPLETION_MARKER);
+        self.store.put(&key, completed_at.to_rfc3339().into_bytes()).await
+    }
+
+    // Deletes run artifacts and cache entries older than the retention window; returns the removed prefixes
+    pub async fn enforce_retention(&self, now: DateTime<Utc>) -> AutomationResult<Vec<String>> {
+        let cutoff = now - Duration::days(self.config.retention_days);
+        let mut expired = Vec::new();
+
+        for key in self.store.list("runs/").await? {
+            let Some(prefix) = key.strip_suffix(COMPLETION_MARKER) else {
+                continue;
+            };
+            let data = self.store.get(&key).await?;
+            let completed = String::from_utf8_lossy(&data)
+                .parse::<DateTime<Utc>>()
+                .map_err(|e| AutomationError::Internal(format!("Corrupt completion marker {}: {}", key, e)))?;
+            if completed < cutoff {
+                expired.push(prefix.to_string());
+            }
+        }
+
+        for key in self.store.list("cache/").await? {
+            let Some(prefix) = key.strip_suffix(CACHE_MANIFEST) else {
+                continue;
+            };
+            let entry: CacheEntry = match serde_json::from_slice(&self.store.get(&key).await?) {
+                Ok(entry) => entry,
+                // Unreadable manifests can never produce a hit, so drop them too
+                Err(_) => {
+                    expired.push(prefix.to_string());
+                    continue;
+                }
+            };
+            if entry.created_at < cutoff {
+                expired.push(prefix.to_string());
+            }
+        }
+
+        for prefix in &expired {
+            let deleted = self.store.delete_prefix(prefix).await?;
+            info!("Retention removed {} objects under {}", deleted, prefix);
+        }
+        Ok(expired)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workflow::{Artifact, ResourceRequirements, TaskConfig, TaskType};
+
+    fn task(id: &str, artifacts: Vec<Artifact>) -> Task {
+        Task {
+            id: id.to_string(),
+            name: id.to_string(),
+            task_type: TaskType::Script { runtime: "bash".into() },
+            config: TaskConfig {
+                inputs: HashMap::from([("target".to_string(), Value::String("linux".into()))]),
+                environment: HashMap::new(),
+                resources: ResourceRequirements {
+                    cpu: "1".into(),
+                    memory: "1Gi".into(),
+                    storage: None,
+                    gpu: None,
+                },
+                secrets: Vec::new(),
+                artifacts,
+            },
+            dependencies: Vec::new(),
+            retry_policy: None,
+            timeout: None,
+            on_failure: None,
+            conditions: Vec::new(),
+        }
+    }
+
+    fn artifact(name: &str, path: &str, type_: ArtifactType) -> Artifact {
+        Artifact {
+            name: name.to_string(),
+            path: path.to_string(),
+            type_,
+        }
+    }
+
+    fn manager(root: &Path, config: ArtifactConfig) -> ArtifactManager {
+        ArtifactManager::new(Arc::new(LocalObjectStore::new(root.join("store"))), config)
+    }
+
+    #[test]
+    fn test_resolve_path() {
+        let dir = Path::new("/tmp/work/run-1/build");
+        assert_eq!(resolve_path(dir, "/workspace/out/app.tar").unwrap(), dir.join("out/app.tar"));
+        assert_eq!(resolve_path(dir, "out/app.tar").unwrap(), dir.join("out/app.tar"));
+        assert_eq!(resolve_path(dir, "/var/data/app.tar").unwrap(), PathBuf::from("/var/data/app.tar"));
+        assert!(resolve_path(dir, "../escape").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_size_limit_and_checksum_verification() {
+        let root = tempfile::tempdir().unwrap();
+        let work = root.path().join("work");
+        tokio::fs::create_dir_all(&work).await.unwrap();
+        tokio::fs::write(work.join("small.bin"), b"0123").await.unwrap();
+        tokio::fs::write(work.join("large.bin"), vec![0u8; 64]).await.unwrap();
+
+        let artifacts = manager(root.path(), ArtifactConfig::default().with_max_artifact_bytes(16));
+        let producer = task("build", vec![artifact("small", "small.bin", ArtifactType::Output)]);
+        let uploaded = artifacts.upload_outputs("run-1", &producer, &work).await.unwrap();
+        assert_eq!(uploaded[0].key, "runs/run-1/build/small");
+        assert_eq!(uploaded[0].size_bytes, 4);
+
+        let oversized = task("build", vec![artifact("large", "large.bin", ArtifactType::Output)]);
+        let err = artifacts.upload_outputs("run-1", &oversized, &work).await.unwrap_err();
+        assert!(matches!(err, AutomationError::Validation(_)));
+
+        // Tamper with the stored copy; the consumer must refuse it
+        tokio::fs::write(root.path().join("store/runs/run-1/build/small"), b"9999").await.unwrap();
+        let consumer = task("deploy", vec![artifact("small", "in/small.bin", ArtifactType::Input)]);
+        let available = HashMap::from([("small".to_string(), uploaded[0].clone())]);
+        let err = artifacts.download_inputs(&consumer, &work, &available).await.unwrap_err();
+        assert!(err.to_string().contains("Checksum mismatch"));
+    }
+
+    #[tokio::test]
+    async fn test_cache_key_tracks_inputs() {
+        let root = tempfile::tempdir().unwrap();
+        let artifacts = manager(root.path(), ArtifactConfig::default());
+        let mut cached = task("build", vec![artifact("bundle", "bundle.tar", ArtifactType::Cache)]);
+
+        assert!(artifacts.cache_key(&task("build", Vec::new()), &[]).is_none());
+        let first = artifacts.cache_key(&cached, &[]).unwrap();
+        assert_eq!(artifacts.cache_key(&cached, &[]).unwrap(), first);
+
+        cached.config.inputs.insert("target".into(), Value::String("darwin".into()));
+        assert_ne!(artifacts.cache_key(&cached, &[]).unwrap(), first);
+    }
+
+    #[tokio::test]
+    async fn test_retention_removes_expired_runs_only() {
+        let root = tempfile::tempdir().unwrap();
+        let work = root.path().join("work");
+        tokio::fs::create_dir_all(&work).await.unwrap();
+        tokio::fs::write(work.join("log.txt"), b"done").await.unwrap();
+
+        let artifacts = manager(root.path(), ArtifactConfig::default().with_retention_days(3));
+        let producer = task("build", vec![artifact("log", "log.txt", ArtifactType::Output)]);
+        let now = Utc::now();
+        for (run_id, age_days) in [("old", 5), ("recent", 1)] {
+            artifacts.upload_outputs(run_id, &producer, &work).await.unwrap();
+            artifacts.complete_run(run_id, now - Duration::days(age_days)).await.unwrap();
+        }
+        // Runs still in progress have no completion marker and are never expired
+        artifacts.upload_outputs("running", &producer, &work).await.unwrap();
+
+        let expired = artifacts.enforce_retention(now).await.unwrap();
+        assert_eq!(expired, vec!["runs/old/".to_string()]);
+
+        let store = LocalObjectStore::new(root.path().join("store"));
+        assert!(store.list("runs/old/").await.unwrap().is_empty());
+        assert!(store.exists("runs/recent/build/log").await.unwrap());
+        assert!(store.exists("runs/running/build/log").await.unwrap());
+    }
+}
@@ -0,0 +1,119 @@
+use std::path::{Component, Path, PathBuf};
+
+use async_trait::async_trait;
+
+use crate::error::{AutomationError, AutomationResult};
+
+#[async_trait]
+pub trait ObjectStore: Send + Sync {
+    async fn put(&self, key: &str, data: Vec<u8>) -> AutomationResult<()>;
+    async fn get(&self, key: &str) -> AutomationResult<Vec<u8>>;
+    async fn exists(&self, key: &str) -> AutomationResult<bool>;
+    async fn list(&self, prefix: &str) -> AutomationResult<Vec<String>>;
+    async fn delete_prefix(&self, prefix: &str) -> AutomationResult<usize>;
+}
+
+fn io_error(context: &str, e: std::io::Error) -> AutomationError {
+    match e.kind() {
+        std::io::ErrorKind::NotFound => AutomationError::NotFound(format!("{}: {}", context, e)),
+        _ => AutomationError::Internal(format!("{}: {}", context, e)),
+    }
+}
+
+// Filesystem-backed store; keys map to paths under the root directory
+pub struct LocalObjectStore {
+    root: PathBuf,
+}
+
+impl LocalObjectStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path_for(&self, key: &str) -> AutomationResult<PathBuf> {
+        let relative = Path::new(key);
+        if !relative.components().all(|c| matches!(c, Component::Normal(_))) {
+            return Err(AutomationError::Validation(format!("Invalid object key '{}'", key)));
+        }
+        Ok(self.root.join(relative))
+    }
+
+    fn key_for(&self, path: &Path) -> Option<String> {
+        let relative = path.strip_prefix(&self.root).ok()?;
+        let parts: Vec<&str> = relative.components().filter_map(|c| c.as_os_str().to_str()).collect();
+        Some(parts.join("/"))
+    }
+
+    async fn walk(&self, dir: PathBuf, keys: &mut Vec<String>) -> AutomationResult<()> {
+        let mut pending = vec![dir];
+        while let Some(dir) = pending.pop() {
+            let mut entries = match tokio::fs::read_dir(&dir).await {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(io_error("Failed to list objects", e)),
+            };
+            while let Some(entry) = entries
+                .next_entry()
+                .await
+                .map_err(|e| io_error("Failed to list objects", e))?
+            {
+                let path = entry.path();
+                if path.is_dir() {
+                    pending.push(path);
+                } else if let Some(key) = self.key_for(&path) {
+                    keys.push(key);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ObjectStore for LocalObjectStore {
+    async fn put(&self, key: &str, data: Vec<u8>) -> AutomationResult<()> {
+        let path = self.path_for(key)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|e| io_error("Failed to create object directory", e))?;
+        }
+        // Write-then-rename so readers never observe a partial object
+        let mut staging = path.clone().into_os_string();
+        staging.push(".partial");
+        tokio::fs::write(&staging, data)
+            .await
+            .map_err(|e| io_error(&format!("Failed to write object {}", key), e))?;
+        tokio::fs::rename(&staging, &path)
+            .await
+            .map_err(|e| io_error(&format!("Failed to write object {}", key), e))
+    }
+
+    async fn get(&self, key: &str) -> AutomationResult<Vec<u8>> {
+        tokio::fs::read(self.path_for(key)?)
+            .await
+            .map_err(|e| io_error(&format!("Failed to read object {}", key), e))
+    }
+
+    async fn exists(&self, key: &str) -> AutomationResult<bool> {
+        Ok(tokio::fs::try_exists(self.path_for(key)?).await.unwrap_or(false))
+    }
+
+    async fn list(&self, prefix: &str) -> AutomationResult<Vec<String>> {
+        let mut keys = Vec::new();
+        self.walk(self.root.clone(), &mut keys).await?;
+        keys.retain(|k| k.starts_with(prefix));
+        keys.sort();
+        Ok(keys)
+    }
+
+    async fn delete_prefix(&self, prefix: &str) -> AutomationResult<usize> {
+        let keys = self.list(prefix).await?;
+        for key in &keys {
+            tokio::fs::remove_file(self.path_for(key)?)
+                .await
+                .map_err(|e| io_error(&format!("Failed to delete object {}", key), e))?;
+        }
+        Ok(keys.len())
+    }
+}
@@ -0,0 +1,790 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::sync::Arc;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sirsi_compute_manager::fleet::{FleetConfig, FleetStore};
+use sirsi_network_services::dns::{RecordSet, RecordSetManager};
+use sirsi_network_services::policy::{SecurityGroup, SecurityGroupManager};
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+use crate::environment::{diff_configs, ConfigChange};
+use crate::error::{AutomationError, AutomationResult};
+
+// Tag on security groups and label on fleets naming the manifest that owns them; owned resources
+// dropped from the manifest get deleted
+pub const MANIFEST_TAG: &str = "sirsi.io/manifest";
+
+const DEFAULT_PLAN_TTL_MINUTES: i64 = 30;
+
+// Desired state, usually checked into git as YAML. Security groups are referred to by name from
+// fleets and from other groups' rules; their ids, and those of record sets, are ignored.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub name: String,
+    // Matched to live groups by VPC and name
+    #[serde(default)]
+    pub security_groups: Vec<SecurityGroup>,
+    #[serde(default)]
+    pub fleets: Vec<FleetConfig>,
+    // Matched to live records by zone, name and type
+    #[serde(default)]
+    pub record_sets: Vec<RecordSet>,
+    // Zones the manifest owns outright: their record sets that aren't in it get deleted
+    #[serde(default)]
+    pub record_zones: Vec<String>,
+}
+
+impl Manifest {
+    pub fn from_yaml(yaml: &str) -> AutomationResult<Self> {
+        serde_yaml::from_str(yaml).map_err(|e| AutomationError::Validation(format!("Invalid manifest: {}", e)))
+    }
+
+    fn validate(&self) -> AutomationResult<()> {
+        let valid = !self.name.is_empty() && self.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(AutomationError::Validation(format!("Invalid manifest name '{}'", self.name)));
+        }
+        let mut seen = BTreeSet::new();
+        let names = self.security_groups.iter().map(|g| format!("security group {}", g.name));
+        let fleets = self.fleets.iter().map(|f| format!("fleet {}", f.id));
+        let records = self.record_sets.iter().map(|r| format!("record set {}", ResourceAddress::record_set(r).name));
+        for resource in names.chain(fleets).chain(records) {
+            if !seen.insert(resource.clone()) {
+                return Err(AutomationError::Validation(format!("Manifest {} declares {} twice", self.name, resource)));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Also the order resources are applied in when nothing else decides it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResourceKind {
+    SecurityGroup,
+    Fleet,
+    RecordSet,
+}
+
+impl ResourceKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ResourceKind::SecurityGroup => "security_group",
+            ResourceKind::Fleet => "fleet",
+            ResourceKind::RecordSet => "record_set",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct ResourceAddress {
+    pub kind: ResourceKind,
+    // `<vpc>/<name>` for groups, the id for fleets and `<zone>/<name>/<type>` for record sets
+    pub name: String,
+}
+
+impl ResourceAddress {
+    fn security_group(group: &SecurityGroup) -> Self {
+        Self { kind: ResourceKind::SecurityGroup, name: format!("{}/{}", group.vpc_id, group.name) }
+    }
+
+    fn fleet(fleet: &FleetConfig) -> Self {
+        Self { kind: ResourceKind::Fleet, name: fleet.id.clone() }
+    }
+
+    fn record_set(record: &RecordSet) -> Self {
+        Self { kind: ResourceKind::RecordSet, name: format!("{}/{}/{:?}", record.zone_id, record.name, record.record_type) }
+    }
+}
+
+impl fmt::Display for ResourceAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.kind.as_str(), self.name)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "spec", rename_all = "snake_case")]
+pub enum Resource {
+    SecurityGroup(SecurityGroup),
+    Fleet(FleetConfig),
+    RecordSet(RecordSet),
+}
+
+impl Resource {
+    // Security group names or ids this resource points at
+    fn group_refs(&self) -> Vec<&String> {
+        match self {
+            Resource::SecurityGroup(group) => group
+                .ingress_rules
+                .iter()
+                .chain(&group.egress_rules)
+                .flat_map(|rule| &rule.source_groups)
+                .collect(),
+            Resource::Fleet(fleet) => fleet.network_config.security_groups.iter().collect(),
+            Resource::RecordSet(_) => Vec::new(),
+        }
+    }
+
+    fn references(&self, lookup: &HashMap<String, ResourceAddress>) -> Vec<ResourceAddress> {
+        self.group_refs().into_iter().filter_map(|r| lookup.get(r).cloned()).collect()
+    }
+
+    // Group names swapped for ids where the group exists
+    fn resolved(&self, ids: &HashMap<String, String>) -> Resource {
+        let resolve = |refs: &mut Vec<String>| {
+            for r in refs.iter_mut() {
+                if let Some(id) = ids.get(r.as_str()) {
+                    *r = id.clone();
+                }
+            }
+        };
+        let mut resource = self.clone();
+        match &mut resource {
+            Resource::SecurityGroup(group) => {
+                for rule in group.ingress_rules.iter_mut().chain(group.egress_rules.iter_mut()) {
+                    resolve(&mut rule.source_groups);
+                }
+            }
+            Resource::Fleet(fleet) => resolve(&mut fleet.network_config.security_groups),
+            Resource::RecordSet(_) => {}
+        }
+        resource
+    }
+
+    // What's compared between desired and live: everything but provider-assigned ids
+    fn comparable(&self) -> Value {
+        let mut value = match self {
+            Resource::SecurityGroup(group) => serde_json::to_value(group),
+            Resource::Fleet(fleet) => serde_json::to_value(fleet),
+            Resource::RecordSet(record) => serde_json::to_value(record),
+        }
+        .unwrap_or(Value::Null);
+        if !matches!(self, Resource::Fleet(_)) {
+            if let Some(fields) = value.as_object_mut() {
+                fields.remove("id");
+            }
+        }
+        for rules in ["ingress_rules", "egress_rules"] {
+            if let Some(Value::Array(rules)) = value.get_mut(rules) {
+                rules.iter_mut().filter_map(Value::as_object_mut).for_each(|rule| {
+                    rule.remove("id");
+                });
+            }
+        }
+        value
+    }
+
+    fn snapshot(&self) -> Value {
+        serde_json::to_value(self).unwrap_or(Value::Null)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeAction {
+    Create,
+    Update,
+    Delete,
+}
+
+impl ChangeAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChangeAction::Create => "create",
+            ChangeAction::Update => "update",
+            ChangeAction::Delete => "delete",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlannedChange {
+    pub address: ResourceAddress,
+    pub action: ChangeAction,
+    // From the live resource to the desired one, ids left out
+    pub changes: Vec<ConfigChange>,
+    // Changes that are applied before this one
+    pub depends_on: Vec<ResourceAddress>,
+    // As declared, group references still by name; None for deletes
+    pub desired: Option<Resource>,
+    // None for creates
+    pub current: Option<Resource>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Plan {
+    pub id: String,
+    pub manifest: Manifest,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    // In the order they'll be applied
+    pub changes: Vec<PlannedChange>,
+    // Live state of everything the plan considered, by address; null where nothing existed.
+    // Applying re-reads it and refuses if anything moved.
+    pub observed: BTreeMap<String, Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum ChangeStatus {
+    Applied,
+    Failed { error: String },
+    // Not attempted because an earlier change failed
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChangeResult {
+    pub address: ResourceAddress,
+    pub action: ChangeAction,
+    pub status: ChangeStatus,
+}
+
+// Per-change outcome of an apply; when it stopped on a failure, the applied changes are the
+// state that was left behind
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApplyReport {
+    pub plan_id: String,
+    pub results: Vec<ChangeResult>,
+    pub completed: bool,
+}
+
+struct LiveState {
+    groups: Vec<SecurityGroup>,
+    fleets: Vec<FleetConfig>,
+    records: Vec<RecordSet>,
+}
+
+type Pairs = BTreeMap<ResourceAddress, (Option<Resource>, Option<Resource>)>;
+
+// Desired and live resource at each address the manifest covers: its own resources plus the
+// live ones it owns
+fn pair(manifest: &Manifest, live: &LiveState) -> Pairs {
+    let mut pairs: Pairs = BTreeMap::new();
+    let owned = |labels: &HashMap<String, String>| labels.get(MANIFEST_TAG) == Some(&manifest.name);
+
+    for group in live.groups.iter().filter(|g| owned(&g.tags)) {
+        pairs.entry(ResourceAddress::security_group(group)).or_default().1 = Some(Resource::SecurityGroup(group.clone()));
+    }
+    for group in &manifest.security_groups {
+        let mut desired = group.clone();
+        desired.tags.insert(MANIFEST_TAG.to_string(), manifest.name.clone());
+        let current = live.groups.iter().find(|g| g.vpc_id == group.vpc_id && g.name == group.name);
+        *pairs.entry(ResourceAddress::security_group(group)).or_default() =
+            (Some(Resource::SecurityGroup(desired)), current.cloned().map(Resource::SecurityGroup));
+    }
+
+    for fleet in live.fleets.iter().filter(|f| owned(&f.labels)) {
+        pairs.entry(ResourceAddress::fleet(fleet)).or_default().1 = Some(Resource::Fleet(fleet.clone()));
+    }
+    for fleet in &manifest.fleets {
+        let mut desired = fleet.clone();
+        desired.labels.insert(MANIFEST_TAG.to_string(), manifest.name.clone());
+        let current = live.fleets.iter().find(|f| f.id == fleet.id);
+        *pairs.entry(ResourceAddress::fleet(fleet)).or_default() =
+            (Some(Resource::Fleet(desired)), current.cloned().map(Resource::Fleet));
+    }
+
+    for record in live.records.iter().filter(|r| manifest.record_zones.contains(&r.zone_id)) {
+        pairs.entry(ResourceAddress::record_set(record)).or_default().1 = Some(Resource::RecordSet(record.clone()));
+    }
+    for record in &manifest.record_sets {
+        let address = ResourceAddress::record_set(record);
+        let current = live.records.iter().find(|r| ResourceAddress::record_set(r) == address);
+        *pairs.entry(address).or_default() = (Some(Resource::RecordSet(record.clone())), current.cloned().map(Resource::RecordSet));
+    }
+    pairs
+}
+
+fn observed(pairs: &Pairs) -> BTreeMap<String, Value> {
+    pairs
+        .iter()
+        .map(|(address, (_, current))| (address.to_string(), current.as_ref().map_or(Value::Null, Resource::snapshot)))
+        .collect()
+}
+
+// Manifest group names and live group ids, to the group's address
+fn reference_lookup(manifest: &Manifest, live: &LiveState) -> HashMap<String, ResourceAddress> {
+    let mut lookup: HashMap<String, ResourceAddress> =
+        live.groups.iter().map(|g| (g.id.clone(), ResourceAddress::security_group(g))).collect();
+    for group in &manifest.security_groups {
+        lookup.insert(group.name.clone(), ResourceAddress::security_group(group));
+    }
+    lookup
+}
+
+// Manifest group names to the ids of the live groups they match
+fn group_ids(manifest: &Manifest, live: &LiveState) -> HashMap<String, String> {
+    manifest
+        .security_groups
+        .iter()
+        .filter_map(|group| {
+            let current = live.groups.iter().find(|g| g.vpc_id == group.vpc_id && g.name == group.name)?;
+            Some((group.name.clone(), current.id.clone()))
+        })
+        .collect()
+}
+
+// Creates and updates wait for the groups they reference; deleting a group waits for everything
+// that referenced it to be updated or deleted
+fn order(changes: Vec<PlannedChange>, lookup: &HashMap<String, ResourceAddress>) -> AutomationResult<Vec<PlannedChange>> {
+    let actions: HashMap<ResourceAddress, ChangeAction> = changes.iter().map(|c| (c.address.clone(), c.action)).collect();
+    let mut depends: BTreeMap<ResourceAddress, BTreeSet<ResourceAddress>> =
+        changes.iter().map(|c| (c.address.clone(), BTreeSet::new())).collect();
+    for change in &changes {
+        for target in change.desired.iter().flat_map(|d| d.references(lookup)) {
+            let pending = matches!(actions.get(&target), Some(ChangeAction::Create | ChangeAction::Update));
+            if pending && target != change.address {
+                depends.entry(change.address.clone()).or_default().insert(target);
+            }
+        }
+        for target in change.current.iter().flat_map(|c| c.references(lookup)) {
+            if actions.get(&target) == Some(&ChangeAction::Delete) && target != change.address {
+                depends.entry(target).or_default().insert(change.address.clone());
+            }
+        }
+    }
+
+    let mut by_address: HashMap<ResourceAddress, PlannedChange> = changes.into_iter().map(|c| (c.address.clone(), c)).collect();
+    let mut pending = depends.clone();
+    let mut ordered = Vec::new();
+    while !pending.is_empty() {
+        let ready: Vec<ResourceAddress> = pending.iter().filter(|(_, deps)| deps.is_empty()).map(|(a, _)| a.clone()).collect();
+        if ready.is_empty() {
+            let cycle: Vec<String> = pending.keys().map(ToString::to_string).collect();
+            return Err(AutomationError::Validation(format!("Dependency cycle between {}", cycle.join(", "))));
+        }
+        for address in ready {
+            pending.remove(&address);
+            pending.values_mut().for_each(|deps| {
+                deps.remove(&address);
+            });
+            if let Some(mut change) = by_address.remove(&address) {
+                change.depends_on = depends[&address].iter().cloned().collect();
+                ordered.push(change);
+            }
+        }
+    }
+    Ok(ordered)
+}
+
+// Converges fleets, security groups and DNS records to manifests through their managers.
+// Plans are kept in memory until applied or expired.
+pub struct ManifestEngine {
+    security_groups: Arc<dyn SecurityGroupManager>,
+    fleets: Arc<dyn FleetStore>,
+    records: Arc<dyn RecordSetManager>,
+    plan_ttl: Duration,
+    // Held through applies so two never interleave
+    plans: Mutex<HashMap<String, Plan>>,
+}
+
+impl ManifestEngine {
+    pub fn new(
+        security_groups: Arc<dyn SecurityGroupManager>,
+        fleets: Arc<dyn FleetStore>,
+        records: Arc<dyn RecordSetManager>,
+    ) -> Self {
+        Self {
+            security_groups,
+            fleets,
+            records,
+            plan_ttl: Duration::minutes(DEFAULT_PLAN_TTL_MINUTES),
+            plans: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn with_plan_ttl(mut self, ttl: Duration) -> Self {
+        self.plan_ttl = ttl;
+        self
+    }
+
+    async fn observe(&self, manifest: &Manifest) -> AutomationResult<LiveState> {
+        let groups = self.security_groups.list_security_groups().await?;
+        let fleets = self.fleets.list_fleets().await?;
+        let zones: BTreeSet<&String> = manifest.record_sets.iter().map(|r| &r.zone_id).chain(&manifest.record_zones).collect();
+        let mut records = Vec::new();
+        for zone in zones {
+            records.extend(self.records.list_record_sets(zone).await?);
+        }
+        Ok(LiveState { groups, fleets, records })
+    }
+
+    // Diffs the manifest against live state into an ordered change set; nothing is changed
+    pub async fn plan(&self, manifest: Manifest) -> AutomationResult<Plan> {
+        manifest.validate()?;
+        let live = self.observe(&manifest).await?;
+        let pairs = pair(&manifest, &live);
+        let ids = group_ids(&manifest, &live);
+
+        let mut changes = Vec::new();
+        for (address, (desired, current)) in &pairs {
+            let action = match (desired, current) {
+                (Some(_), None) => ChangeAction::Create,
+                (None, Some(_)) => ChangeAction::Delete,
+                (Some(_), Some(_)) => ChangeAction::Update,
+                (None, None) => continue,
+            };
+            let diff = diff_configs(
+                current.as_ref().map(Resource::comparable).as_ref(),
+                desired.as_ref().map(|d| d.resolved(&ids).comparable()).as_ref(),
+            );
+            if diff.is_empty() {
+                continue;
+            }
+            changes.push(PlannedChange {
+                address: address.clone(),
+                action,
+                changes: diff,
+                depends_on: Vec::new(),
+                desired: desired.clone(),
+                current: current.clone(),
+            });
+        }
+
+        let now = Utc::now();
+        let plan = Plan {
+            id: uuid::Uuid::new_v4().to_string(),
+            changes: order(changes, &reference_lookup(&manifest, &live))?,
+            observed: observed(&pairs),
+            manifest,
+            created_at: now,
+            expires_at: now + self.plan_ttl,
+        };
+        info!("Planned {} changes for manifest {} as plan {}", plan.changes.len(), plan.manifest.name, plan.id);
+
+        let mut plans = self.plans.lock().await;
+        plans.retain(|_, p| p.expires_at > now);
+        plans.insert(plan.id.clone(), plan.clone());
+        Ok(plan)
+    }
+
+    // Runs a plan's changes in order, stopping at the first failure. A plan is used up by
+    // applying it, whatever the outcome.
+    pub async fn apply(&self, plan_id: &str) -> AutomationResult<ApplyReport> {
+        let mut plans = self.plans.lock().await;
+        let plan = plans.remove(plan_id).ok_or_else(|| AutomationError::NotFound(format!("Plan {} not found", plan_id)))?;
+        if Utc::now() >= plan.expires_at {
+            return Err(AutomationError::Conflict(format!("Plan {} expired at {}; plan again", plan_id, plan.expires_at)));
+        }
+        let live = match self.observe(&plan.manifest).await {
+            Ok(live) => live,
+            Err(e) => {
+                // Nothing was touched, so the plan is still good to retry
+                plans.insert(plan.id.clone(), plan);
+                return Err(e);
+            }
+        };
+        let now_observed = observed(&pair(&plan.manifest, &live));
+        let moved: BTreeSet<&String> = plan
+            .observed
+            .keys()
+            .chain(now_observed.keys())
+            .filter(|address| plan.observed.get(*address) != now_observed.get(*address))
+            .collect();
+        if !moved.is_empty() {
+            let moved: Vec<&str> = moved.into_iter().map(String::as_str).collect();
+            return Err(AutomationError::Conflict(format!(
+                "Live state changed since plan {} was made ({}); plan again",
+                plan_id,
+                moved.join(", ")
+            )));
+        }
+
+        let mut ids = group_ids(&plan.manifest, &live);
+        let mut results = Vec::new();
+        let mut failed = false;
+        for change in &plan.changes {
+            let status = if failed {
+                ChangeStatus::Skipped
+            } else {
+                match self.apply_change(change, &mut ids).await {
+                    Ok(()) => ChangeStatus::Applied,
+                    Err(e) => {
+                        warn!("Plan {} stopped at {} {}: {}", plan_id, change.address, change.action.as_str(), e);
+                        failed = true;
+                        ChangeStatus::Failed { error: e.to_string() }
+                    }
+                }
+            };
+            results.push(ChangeResult { address: change.address.clone(), action: change.action, status });
+        }
+        info!("Applied plan {} for manifest {}{}", plan_id, plan.manifest.name, if failed { " partially" } else { "" });
+        Ok(ApplyReport { plan_id: plan.id.clone(), results, completed: !failed })
+    }
+
+    async fn apply_change(&self, change: &PlannedChange, ids: &mut HashMap<String, String>) -> AutomationResult<()> {
+        if change.action == ChangeAction::Delete {
+            return match &change.current {
+                Some(Resource::SecurityGroup(group)) => Ok(self.security_groups.delete_security_group(&group.id).await?),
+                Some(Resource::Fleet(fleet)) => Ok(self.fleets.delete_fleet(&fleet.id).await?),
+                Some(Resource::RecordSet(record)) => Ok(self.records.delete_record_set(&record.zone_id, &record.id).await?),
+                None => Err(AutomationError::Internal(format!("Delete of {} has no live resource", change.address))),
+            };
+        }
+        let desired = change
+            .desired
+            .as_ref()
+            .ok_or_else(|| AutomationError::Internal(format!("{} of {} has no desired resource", change.action.as_str(), change.address)))?;
+        match (desired.resolved(ids), &change.current) {
+            (Resource::SecurityGroup(group), None) => {
+                let name = group.name.clone();
+                let created = self.security_groups.create_security_group(group).await?;
+                ids.insert(name, created.id);
+            }
+            (Resource::SecurityGroup(mut group), Some(Resource::SecurityGroup(current))) => {
+                group.id = current.id.clone();
+                self.security_groups.update_security_group(group).await?;
+            }
+            (Resource::Fleet(fleet), _) => self.fleets.save_fleet(&fleet).await?,
+            (Resource::RecordSet(record), None) => {
+                self.records.create_record_set(record).await?;
+            }
+            (Resource::RecordSet(mut record), Some(Resource::RecordSet(current))) => {
+                record.id = current.id.clone();
+                self.records.modify_record_set(record).await?;
+            }
+            _ => return Err(AutomationError::Internal(format!("Live resource at {} is of another kind", change.address))),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sirsi_compute_manager::fleet::InMemoryFleetStore;
+    use sirsi_network_services::dns::RecordType;
+    use sirsi_network_services::policy::{Protocol, SecurityGroupRule};
+    use sirsi_network_services::{NetworkError, NetworkResult};
+    use std::sync::Mutex as StdMutex;
+
+    #[derive(Default)]
+    struct Groups(StdMutex<BTreeMap<String, SecurityGroup>>);
+
+    #[async_trait::async_trait]
+    impl SecurityGroupManager for Groups {
+        async fn create_security_group(&self, mut group: SecurityGroup) -> NetworkResult<SecurityGroup> {
+            let mut groups = self.0.lock().unwrap();
+            group.id = format!("sg-{}", groups.len() + 1);
+            groups.insert(group.id.clone(), group.clone());
+            Ok(group)
+        }
+
+        async fn update_security_group(&self, group: SecurityGroup) -> NetworkResult<SecurityGroup> {
+            self.0.lock().unwrap().insert(group.id.clone(), group.clone());
+            Ok(group)
+        }
+
+        async fn delete_security_group(&self, id: &str) -> NetworkResult<()> {
+            self.0.lock().unwrap().remove(id);
+            Ok(())
+        }
+
+        async fn get_security_group(&self, id: &str) -> NetworkResult<SecurityGroup> {
+            self.0.lock().unwrap().get(id).cloned().ok_or_else(|| NetworkError::NotFound(id.into()))
+        }
+
+        async fn list_security_groups(&self) -> NetworkResult<Vec<SecurityGroup>> {
+            Ok(self.0.lock().unwrap().values().cloned().collect())
+        }
+    }
+
+    #[derive(Default)]
+    struct Records {
+        records: StdMutex<Vec<RecordSet>>,
+        reject: Option<String>,
+    }
+
+    #[async_trait::async_trait]
+    impl RecordSetManager for Records {
+        async fn create_record_set(&self, mut record: RecordSet) -> NetworkResult<RecordSet> {
+            if self.reject.as_ref() == Some(&record.name) {
+                return Err(NetworkError::Dns(format!("{} rejected", record.name)));
+            }
+            let mut records = self.records.lock().unwrap();
+            record.id = format!("rec-{}", records.len() + 1);
+            records.push(record.clone());
+            Ok(record)
+        }
+
+        async fn modify_record_set(&self, record: RecordSet) -> NetworkResult<RecordSet> {
+            let mut records = self.records.lock().unwrap();
+            records.retain(|r| r.id != record.id);
+            records.push(record.clone());
+            Ok(record)
+        }
+
+        async fn delete_record_set(&self, _zone_id: &str, record_id: &str) -> NetworkResult<()> {
+            self.records.lock().unwrap().retain(|r| r.id != record_id);
+            Ok(())
+        }
+
+        async fn get_record_set(&self, _zone_id: &str, record_id: &str) -> NetworkResult<RecordSet> {
+            let records = self.records.lock().unwrap();
+            records.iter().find(|r| r.id == record_id).cloned().ok_or_else(|| NetworkError::NotFound(record_id.into()))
+        }
+
+        async fn list_record_sets(&self, zone_id: &str) -> NetworkResult<Vec<RecordSet>> {
+            Ok(self.records.lock().unwrap().iter().filter(|r| r.zone_id == zone_id).cloned().collect())
+        }
+    }
+
+    fn group(name: &str, sources: &[&str]) -> SecurityGroup {
+        SecurityGroup {
+            id: String::new(),
+            name: name.into(),
+            description: format!("{} tier", name),
+            vpc_id: "vpc-shop".into(),
+            ingress_rules: vec![SecurityGroupRule {
+                id: String::new(),
+                description: None,
+                protocol: Protocol::TCP,
+                from_port: Some(443),
+                to_port: Some(443),
+                cidr_blocks: if sources.is_empty() { vec!["0.0.0.0/0".into()] } else { Vec::new() },
+                source_groups: sources.iter().map(|s| s.to_string()).collect(),
+            }],
+            egress_rules: Vec::new(),
+            tags: HashMap::new(),
+        }
+    }
+
+    fn record(name: &str) -> RecordSet {
+        RecordSet {
+            id: String::new(),
+            zone_id: "zone-shop".into(),
+            name: name.into(),
+            record_type: RecordType::A,
+            ttl: 300,
+            records: vec!["10.0.0.10".into()],
+            routing_policy: None,
+            health_check: None,
+            alias_target: None,
+        }
+    }
+
+    fn manifest() -> Manifest {
+        let mut fleet = FleetConfig::new("storefront".into(), "Storefront".into());
+        fleet.network_config.security_groups = vec!["web".into()];
+        Manifest {
+            name: "shop".into(),
+            // The database group is declared first but refers to the web one
+            security_groups: vec![group("db", &["web"]), group("web", &[])],
+            fleets: vec![fleet],
+            record_sets: vec![record("www.shop.example")],
+            record_zones: vec!["zone-shop".into()],
+        }
+    }
+
+    fn engine(records: Records) -> (ManifestEngine, Arc<Groups>, Arc<InMemoryFleetStore>) {
+        let groups = Arc::new(Groups::default());
+        let fleets = Arc::new(InMemoryFleetStore::new());
+        (ManifestEngine::new(groups.clone(), fleets.clone(), Arc::new(records)), groups, fleets)
+    }
+
+    fn addresses(changes: &[PlannedChange]) -> Vec<(String, ChangeAction)> {
+        changes.iter().map(|c| (c.address.to_string(), c.action)).collect()
+    }
+
+    #[tokio::test]
+    async fn test_plan_orders_groups_before_their_users() {
+        let (engine, groups, fleets) = engine(Records::default());
+        let manifest = Manifest::from_yaml(&serde_yaml::to_string(&manifest()).unwrap()).unwrap();
+
+        let plan = engine.plan(manifest.clone()).await.unwrap();
+        assert_eq!(
+            addresses(&plan.changes),
+            vec![
+                ("security_group.vpc-shop/web".to_string(), ChangeAction::Create),
+                ("record_set.zone-shop/www.shop.example/A".to_string(), ChangeAction::Create),
+                ("security_group.vpc-shop/db".to_string(), ChangeAction::Create),
+                ("fleet.storefront".to_string(), ChangeAction::Create),
+            ]
+        );
+        assert_eq!(plan.changes[3].depends_on, vec![ResourceAddress::security_group(&group("web", &[]))]);
+
+        let report = engine.apply(&plan.id).await.unwrap();
+        assert!(report.completed);
+        let web = groups.list_security_groups().await.unwrap().into_iter().find(|g| g.name == "web").unwrap();
+        let db = groups.list_security_groups().await.unwrap().into_iter().find(|g| g.name == "db").unwrap();
+        assert_eq!(db.ingress_rules[0].source_groups, vec![web.id.clone()]);
+        assert_eq!(db.tags.get(MANIFEST_TAG).map(String::as_str), Some("shop"));
+        let fleet = fleets.get_fleet("storefront").await.unwrap().unwrap();
+        assert_eq!(fleet.network_config.security_groups, vec![web.id.clone()]);
+        assert!(matches!(engine.apply(&plan.id).await, Err(AutomationError::NotFound(_))));
+
+        // Converged: planning again finds nothing to do
+        assert!(engine.plan(manifest.clone()).await.unwrap().changes.is_empty());
+
+        // Dropping the web tier deletes its group only after the fleet and the database rule
+        // stop pointing at it
+        let mut smaller = manifest;
+        smaller.fleets.clear();
+        smaller.security_groups = vec![group("db", &[])];
+        let plan = engine.plan(smaller).await.unwrap();
+        assert_eq!(
+            addresses(&plan.changes),
+            vec![
+                ("security_group.vpc-shop/db".to_string(), ChangeAction::Update),
+                ("fleet.storefront".to_string(), ChangeAction::Delete),
+                ("security_group.vpc-shop/web".to_string(), ChangeAction::Delete),
+            ]
+        );
+        assert_eq!(plan.changes[2].depends_on.len(), 2);
+        assert!(engine.apply(&plan.id).await.unwrap().completed);
+        assert_eq!(groups.list_security_groups().await.unwrap().len(), 1);
+        assert!(fleets.list_fleets().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_stale_and_expired_plans_are_refused() {
+        let (engine, groups, _) = engine(Records::default());
+        let plan = engine.plan(manifest()).await.unwrap();
+        assert!(engine.apply(&plan.id).await.unwrap().completed);
+
+        let mut changed = manifest();
+        changed.record_sets[0].ttl = 60;
+        let plan = engine.plan(changed.clone()).await.unwrap();
+        assert_eq!(addresses(&plan.changes), vec![("record_set.zone-shop/www.shop.example/A".to_string(), ChangeAction::Update)]);
+
+        // Someone widens the web group by hand after the plan was made
+        let mut web = groups.list_security_groups().await.unwrap().into_iter().find(|g| g.name == "web").unwrap();
+        web.ingress_rules[0].to_port = Some(8443);
+        groups.update_security_group(web).await.unwrap();
+        let err = engine.apply(&plan.id).await.unwrap_err();
+        assert!(matches!(err, AutomationError::Conflict(_)));
+        assert!(err.to_string().contains("security_group.vpc-shop/web"));
+        assert!(matches!(engine.apply(&plan.id).await, Err(AutomationError::NotFound(_))));
+
+        // Re-planning reverts the manual change along with the TTL
+        let plan = engine.plan(changed.clone()).await.unwrap();
+        assert_eq!(plan.changes.len(), 2);
+
+        let (engine, _, _) = self::engine(Records::default());
+        let engine = engine.with_plan_ttl(Duration::zero());
+        let plan = engine.plan(changed).await.unwrap();
+        let err = engine.apply(&plan.id).await.unwrap_err();
+        assert!(err.to_string().contains("expired"));
+    }
+
+    #[tokio::test]
+    async fn test_failed_change_stops_the_apply() {
+        let (engine, groups, fleets) = engine(Records { reject: Some("www.shop.example".into()), ..Default::default() });
+        let plan = engine.plan(manifest()).await.unwrap();
+        let report = engine.apply(&plan.id).await.unwrap();
+        assert!(!report.completed);
+        let statuses: Vec<&ChangeStatus> = report.results.iter().map(|r| &r.status).collect();
+        assert_eq!(statuses[0], &ChangeStatus::Applied);
+        assert!(matches!(statuses[1], ChangeStatus::Failed { error } if error.contains("rejected")));
+        assert_eq!(statuses[2..], [&ChangeStatus::Skipped, &ChangeStatus::Skipped]);
+        // Only the web group was created before the stop
+        assert_eq!(groups.list_security_groups().await.unwrap().len(), 1);
+        assert!(fleets.list_fleets().await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,796 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+use crate::artifact::ObjectStore;
+use crate::error::{AutomationError, AutomationResult};
+use crate::workflow::Workflow;
+
+const ENVIRONMENT_PREFIX: &str = "environments/";
+
+// A named stage of a project's pipeline, e.g. dev -> staging -> prod
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Environment {
+    pub project_id: String,
+    pub name: String,
+    // `{{ env.<name> }}` placeholders in promoted configs resolve against these
+    pub variables: HashMap<String, Value>,
+    // Promotions into this environment wait for someone other than the promoter to approve
+    pub requires_approval: bool,
+    // Where promotions out of this environment go; None for the last stage
+    pub promotes_to: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Environment {
+    pub fn new(project_id: impl Into<String>, name: impl Into<String>) -> Self {
+        let now = Utc::now();
+        Self {
+            project_id: project_id.into(),
+            name: name.into(),
+            variables: HashMap::new(),
+            requires_approval: false,
+            promotes_to: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    pub fn with_variable(mut self, name: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.variables.insert(name.into(), value.into());
+        self
+    }
+
+    pub fn with_approval(mut self) -> Self {
+        self.requires_approval = true;
+        self
+    }
+
+    pub fn with_promotes_to(mut self, next: impl Into<String>) -> Self {
+        self.promotes_to = Some(next.into());
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PromotionSubject {
+    Workflow { workflow_id: String },
+    MlDeployment { deployment_id: String },
+}
+
+impl PromotionSubject {
+    // Stable name used to route configs to a target
+    pub fn kind(&self) -> &'static str {
+        match self {
+            PromotionSubject::Workflow { .. } => "workflow",
+            PromotionSubject::MlDeployment { .. } => "ml_deployment",
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        match self {
+            PromotionSubject::Workflow { workflow_id } => workflow_id,
+            PromotionSubject::MlDeployment { deployment_id } => deployment_id,
+        }
+    }
+}
+
+// A version of a subject's config, placeholders unrendered, as it moves between environments
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Release {
+    pub version: String,
+    pub config: Value,
+}
+
+impl Release {
+    pub fn new(version: impl Into<String>, config: Value) -> Self {
+        Self { version: version.into(), config }
+    }
+
+    pub fn workflow(workflow: &Workflow) -> AutomationResult<Self> {
+        let config = serde_json::to_value(workflow).map_err(|e| AutomationError::Internal(e.to_string()))?;
+        Ok(Self::new(workflow.version.clone(), config))
+    }
+}
+
+// One differing value, at a JSON pointer into the config. Arrays are compared whole.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConfigChange {
+    pub path: String,
+    pub from: Option<Value>,
+    pub to: Option<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum PromotionStatus {
+    AwaitingApproval,
+    Applied,
+    Rejected { rejected_by: String, reason: String },
+    Failed { error: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromotionApproval {
+    pub approved_by: String,
+    pub approved_at: DateTime<Utc>,
+}
+
+// The recorded event for every config that was, or was meant to be, applied to an environment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Promotion {
+    pub id: String,
+    // Orders a project's promotions
+    pub sequence: u64,
+    pub project_id: String,
+    pub subject: PromotionSubject,
+    pub environment: String,
+    // None when released straight into the environment
+    pub source_environment: Option<String>,
+    // Set on rollbacks to the promotion whose config was re-applied
+    pub restores: Option<String>,
+    pub release: Release,
+    pub rendered: Value,
+    // Against what the environment was running when this was promoted
+    pub changes: Vec<ConfigChange>,
+    pub status: PromotionStatus,
+    pub promoted_by: String,
+    pub promoted_at: DateTime<Utc>,
+    pub approval: Option<PromotionApproval>,
+    pub applied_at: Option<DateTime<Utc>>,
+}
+
+// An environment's live config no longer matching what was last promoted into it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DriftReport {
+    pub subject: PromotionSubject,
+    pub promotion_id: String,
+    pub version: String,
+    // From the promoted config to the live one; a lone change at "/" to nothing means it's gone
+    pub changes: Vec<ConfigChange>,
+}
+
+// Where rendered configs of one subject kind get deployed. Implementations must make `apply`
+// idempotent, since rollbacks re-apply earlier configs.
+#[async_trait]
+pub trait ConfigTarget: Send + Sync {
+    async fn apply(&self, environment: &Environment, subject: &PromotionSubject, config: &Value) -> AutomationResult<()>;
+    // None when nothing is deployed
+    async fn live_config(&self, environment: &Environment, subject: &PromotionSubject) -> AutomationResult<Option<Value>>;
+}
+
+#[async_trait]
+pub trait EnvironmentStore: Send + Sync {
+    async fn save_environment(&self, environment: &Environment) -> AutomationResult<()>;
+    async fn load_environment(&self, project_id: &str, name: &str) -> AutomationResult<Option<Environment>>;
+    async fn list_environments(&self, project_id: &str) -> AutomationResult<Vec<Environment>>;
+    async fn save_promotion(&self, promotion: &Promotion) -> AutomationResult<()>;
+    // Ordered by sequence
+    async fn list_promotions(&self, project_id: &str) -> AutomationResult<Vec<Promotion>>;
+}
+
+// Environments and promotions as JSON objects under `environments/<project>/`
+pub struct ObjectEnvironmentStore {
+    objects: Arc<dyn ObjectStore>,
+}
+
+impl ObjectEnvironmentStore {
+    pub fn new(objects: Arc<dyn ObjectStore>) -> Self {
+        Self { objects }
+    }
+
+    fn environments_prefix(project_id: &str) -> String {
+        format!("{}{}/environments/", ENVIRONMENT_PREFIX, project_id)
+    }
+
+    fn promotions_prefix(project_id: &str) -> String {
+        format!("{}{}/promotions/", ENVIRONMENT_PREFIX, project_id)
+    }
+
+    async fn read<T: serde::de::DeserializeOwned>(&self, key: &str) -> AutomationResult<T> {
+        let data = self.objects.get(key).await?;
+        serde_json::from_slice(&data).map_err(|e| AutomationError::Internal(format!("Corrupt environment object {}: {}", key, e)))
+    }
+
+    async fn write<T: Serialize>(&self, key: &str, value: &T) -> AutomationResult<()> {
+        let data = serde_json::to_vec(value).map_err(|e| AutomationError::Internal(e.to_string()))?;
+        self.objects.put(key, data).await
+    }
+
+    async fn read_all<T: serde::de::DeserializeOwned>(&self, prefix: &str) -> AutomationResult<Vec<T>> {
+        let mut keys = self.objects.list(prefix).await?;
+        keys.sort();
+        let mut values = Vec::new();
+        for key in keys {
+            values.push(self.read(&key).await?);
+        }
+        Ok(values)
+    }
+}
+
+#[async_trait]
+impl EnvironmentStore for ObjectEnvironmentStore {
+    async fn save_environment(&self, environment: &Environment) -> AutomationResult<()> {
+        let key = format!("{}{}.json", Self::environments_prefix(&environment.project_id), environment.name);
+        self.write(&key, environment).await
+    }
+
+    async fn load_environment(&self, project_id: &str, name: &str) -> AutomationResult<Option<Environment>> {
+        let key = format!("{}{}.json", Self::environments_prefix(project_id), name);
+        if !self.objects.exists(&key).await? {
+            return Ok(None);
+        }
+        self.read(&key).await.map(Some)
+    }
+
+    async fn list_environments(&self, project_id: &str) -> AutomationResult<Vec<Environment>> {
+        self.read_all(&Self::environments_prefix(project_id)).await
+    }
+
+    async fn save_promotion(&self, promotion: &Promotion) -> AutomationResult<()> {
+        let key = format!("{}{:08}.json", Self::promotions_prefix(&promotion.project_id), promotion.sequence);
+        self.write(&key, promotion).await
+    }
+
+    async fn list_promotions(&self, project_id: &str) -> AutomationResult<Vec<Promotion>> {
+        let mut promotions: Vec<Promotion> = self.read_all(&Self::promotions_prefix(project_id)).await?;
+        promotions.sort_by_key(|p| p.sequence);
+        Ok(promotions)
+    }
+}
+
+fn validate_name(what: &str, name: &str) -> AutomationResult<()> {
+    let valid = !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(AutomationError::Validation(format!("Invalid {} '{}'", what, name)))
+    }
+}
+
+// `{{ env.name }}` with nothing around it; such strings take the variable's typed value
+fn whole_placeholder(s: &str) -> Option<&str> {
+    let inner = s.trim().strip_prefix("{{")?.strip_suffix("}}")?;
+    if inner.contains("{{") || inner.contains("}}") {
+        return None;
+    }
+    inner.trim().strip_prefix("env.")
+}
+
+fn variable_to_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+// Placeholders with other roots are left for the workflow's own renderers
+fn interpolate(s: &str, variables: &HashMap<String, Value>, missing: &mut BTreeSet<String>) -> String {
+    let mut out = String::new();
+    let mut rest = s;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}").map(|end| start + end) else { break };
+        match rest[start + 2..end].trim().strip_prefix("env.") {
+            Some(name) => {
+                out.push_str(&rest[..start]);
+                match variables.get(name) {
+                    Some(value) => out.push_str(&variable_to_string(value)),
+                    None => {
+                        missing.insert(name.to_string());
+                    }
+                }
+            }
+            None => out.push_str(&rest[..end + 2]),
+        }
+        rest = &rest[end + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+fn substitute(value: &mut Value, variables: &HashMap<String, Value>, missing: &mut BTreeSet<String>) {
+    match value {
+        Value::String(s) => match whole_placeholder(s) {
+            Some(name) => match variables.get(name) {
+                Some(variable) => *value = variable.clone(),
+                None => {
+                    missing.insert(name.to_string());
+                }
+            },
+            None => *s = interpolate(s, variables, missing),
+        },
+        Value::Array(items) => items.iter_mut().for_each(|item| substitute(item, variables, missing)),
+        Value::Object(fields) => fields.values_mut().for_each(|field| substitute(field, variables, missing)),
+        _ => {}
+    }
+}
+
+pub fn render_config(config: &Value, environment: &Environment) -> AutomationResult<Value> {
+    let mut rendered = config.clone();
+    let mut missing = BTreeSet::new();
+    substitute(&mut rendered, &environment.variables, &mut missing);
+    if !missing.is_empty() {
+        return Err(AutomationError::Validation(format!(
+            "Environment {} is missing variables: {}",
+            environment.name,
+            missing.into_iter().collect::<Vec<_>>().join(", ")
+        )));
+    }
+    Ok(rendered)
+}
+
+fn diff_into(path: &str, old: Option<&Value>, new: Option<&Value>, changes: &mut Vec<ConfigChange>) {
+    match (old, new) {
+        (Some(Value::Object(old)), Some(Value::Object(new))) => {
+            let keys: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+            for key in keys {
+                let path = format!("{}/{}", path, key.replace('~', "~0").replace('/', "~1"));
+                diff_into(&path, old.get(key), new.get(key), changes);
+            }
+        }
+        (old, new) if old != new => changes.push(ConfigChange {
+            path: if path.is_empty() { "/".to_string() } else { path.to_string() },
+            from: old.cloned(),
+            to: new.cloned(),
+        }),
+        _ => {}
+    }
+}
+
+pub fn diff_configs(old: Option<&Value>, new: Option<&Value>) -> Vec<ConfigChange> {
+    let mut changes = Vec::new();
+    diff_into("", old, new, &mut changes);
+    changes
+}
+
+pub struct PromotionPipeline {
+    store: Arc<dyn EnvironmentStore>,
+    targets: HashMap<String, Arc<dyn ConfigTarget>>,
+    // Serialises writes so promotion sequences stay unique
+    lock: Mutex<()>,
+}
+
+impl PromotionPipeline {
+    pub fn new(store: Arc<dyn EnvironmentStore>) -> Self {
+        Self { store, targets: HashMap::new(), lock: Mutex::new(()) }
+    }
+
+    pub fn with_target(mut self, kind: impl Into<String>, target: Arc<dyn ConfigTarget>) -> Self {
+        self.targets.insert(kind.into(), target);
+        self
+    }
+
+    fn target(&self, subject: &PromotionSubject) -> AutomationResult<&Arc<dyn ConfigTarget>> {
+        self.targets
+            .get(subject.kind())
+            .ok_or_else(|| AutomationError::Config(format!("No config target registered for {} promotions", subject.kind())))
+    }
+
+    pub async fn create_environment(&self, mut environment: Environment) -> AutomationResult<Environment> {
+        validate_name("project id", &environment.project_id)?;
+        validate_name("environment name", &environment.name)?;
+        let _guard = self.lock.lock().await;
+        if self.store.load_environment(&environment.project_id, &environment.name).await?.is_some() {
+            return Err(AutomationError::Conflict(format!(
+                "Environment {} already exists in project {}",
+                environment.name, environment.project_id
+            )));
+        }
+        let now = Utc::now();
+        environment.created_at = now;
+        environment.updated_at = now;
+        self.store.save_environment(&environment).await?;
+        Ok(environment)
+    }
+
+    // Variable changes take effect with the next promotion; nothing is re-applied
+    pub async fn update_environment(&self, mut environment: Environment) -> AutomationResult<Environment> {
+        let _guard = self.lock.lock().await;
+        let existing = self.get_environment(&environment.project_id, &environment.name).await?;
+        environment.created_at = existing.created_at;
+        environment.updated_at = Utc::now();
+        self.store.save_environment(&environment).await?;
+        Ok(environment)
+    }
+
+    pub async fn get_environment(&self, project_id: &str, name: &str) -> AutomationResult<Environment> {
+        self.store
+            .load_environment(project_id, name)
+            .await?
+            .ok_or_else(|| AutomationError::NotFound(format!("Environment {} not found in project {}", name, project_id)))
+    }
+
+    pub async fn list_environments(&self, project_id: &str) -> AutomationResult<Vec<Environment>> {
+        self.store.list_environments(project_id).await
+    }
+
+    // Oldest first
+    pub async fn history(&self, project_id: &str, environment: &str, subject: &PromotionSubject) -> AutomationResult<Vec<Promotion>> {
+        Ok(self
+            .store
+            .list_promotions(project_id)
+            .await?
+            .into_iter()
+            .filter(|p| p.environment == environment && &p.subject == subject)
+            .collect())
+    }
+
+    // The promotion the environment is running, as far as promotions go
+    pub async fn current(&self, project_id: &str, environment: &str, subject: &PromotionSubject) -> AutomationResult<Option<Promotion>> {
+        let history = self.history(project_id, environment, subject).await?;
+        Ok(history.into_iter().rev().find(|p| p.status == PromotionStatus::Applied))
+    }
+
+    async fn get_promotion(&self, project_id: &str, id: &str) -> AutomationResult<Promotion> {
+        self.store
+            .list_promotions(project_id)
+            .await?
+            .into_iter()
+            .find(|p| p.id == id)
+            .ok_or_else(|| AutomationError::NotFound(format!("Promotion {} not found in project {}", id, project_id)))
+    }
+
+    // Puts a new version straight into an environment, usually the first of the pipeline
+    pub async fn release(
+        &self,
+        project_id: &str,
+        environment: &str,
+        subject: PromotionSubject,
+        release: Release,
+        promoted_by: &str,
+    ) -> AutomationResult<Promotion> {
+        let _guard = self.lock.lock().await;
+        let environment = self.get_environment(project_id, environment).await?;
+        let rendered = render_config(&release.config, &environment)?;
+        self.submit(&environment, subject, release, rendered, None, None, promoted_by).await
+    }
+
+    // Takes what `from` is running and promotes it to the environment after it, rendered with
+    // that environment's variables
+    pub async fn promote(
+        &self,
+        project_id: &str,
+        from: &str,
+        subject: PromotionSubject,
+        promoted_by: &str,
+    ) -> AutomationResult<Promotion> {
+        let _guard = self.lock.lock().await;
+        let source = self.get_environment(project_id, from).await?;
+        let next = source
+            .promotes_to
+            .as_deref()
+            .ok_or_else(|| AutomationError::Validation(format!("Environment {} has nothing to promote to", from)))?;
+        let target = self.get_environment(project_id, next).await?;
+        let validated = self.current(project_id, from, &subject).await?.ok_or_else(|| {
+            AutomationError::NotFound(format!("Nothing of {} {} is applied in {}", subject.kind(), subject.id(), from))
+        })?;
+        let rendered = render_config(&validated.release.config, &target)?;
+        self.submit(&target, subject, validated.release, rendered, Some(from.to_string()), None, promoted_by).await
+    }
+
+    // Re-applies the config of the last applied promotion with a different version. Rollbacks
+    // aren't gated: that version was approved when it was first promoted.
+    pub async fn rollback(
+        &self,
+        project_id: &str,
+        environment: &str,
+        subject: PromotionSubject,
+        promoted_by: &str,
+    ) -> AutomationResult<Promotion> {
+        let _guard = self.lock.lock().await;
+        let environment = self.get_environment(project_id, environment).await?;
+        let applied: Vec<Promotion> = self
+            .history(project_id, &environment.name, &subject)
+            .await?
+            .into_iter()
+            .filter(|p| p.status == PromotionStatus::Applied)
+            .collect();
+        let current = applied.last().ok_or_else(|| {
+            AutomationError::NotFound(format!("Nothing of {} {} is applied in {}", subject.kind(), subject.id(), environment.name))
+        })?;
+        let previous = applied
+            .iter()
+            .rev()
+            .find(|p| p.release.version != current.release.version)
+            .ok_or_else(|| {
+                AutomationError::Conflict(format!("{} has no earlier version of {} {} to roll back to", environment.name, subject.kind(), subject.id()))
+            })?
+            .clone();
+        let restores = Some(previous.id.clone());
+        self.submit(&environment, subject, previous.release, previous.rendered, None, restores, promoted_by).await
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn submit(
+        &self,
+        environment: &Environment,
+        subject: PromotionSubject,
+        release: Release,
+        rendered: Value,
+        source_environment: Option<String>,
+        restores: Option<String>,
+        promoted_by: &str,
+    ) -> AutomationResult<Promotion> {
+        self.target(&subject)?;
+        let promotions = self.store.list_promotions(&environment.project_id).await?;
+        let pending = promotions.iter().any(|p| {
+            p.environment == environment.name && p.subject == subject && p.status == PromotionStatus::AwaitingApproval
+        });
+        if pending {
+            return Err(AutomationError::Conflict(format!(
+                "A promotion of {} {} into {} is already waiting for approval",
+                subject.kind(),
+                subject.id(),
+                environment.name
+            )));
+        }
+        let running = promotions
+            .iter()
+            .rev()
+            .find(|p| p.environment == environment.name && p.subject == subject && p.status == PromotionStatus::Applied);
+        let gated = environment.requires_approval && restores.is_none();
+        let mut promotion = Promotion {
+            id: uuid::Uuid::new_v4().to_string(),
+            sequence: promotions.last().map_or(0, |p| p.sequence) + 1,
+            project_id: environment.project_id.clone(),
+            changes: diff_configs(running.map(|p| &p.rendered), Some(&rendered)),
+            subject,
+            environment: environment.name.clone(),
+            source_environment,
+            restores,
+            release,
+            rendered,
+            status: if gated { PromotionStatus::AwaitingApproval } else { PromotionStatus::Applied },
+            promoted_by: promoted_by.to_string(),
+            promoted_at: Utc::now(),
+            approval: None,
+            applied_at: None,
+        };
+        if gated {
+            self.store.save_promotion(&promotion).await?;
+            info!(
+                "Promotion {} of {} {} into {} is waiting for approval",
+                promotion.id,
+                promotion.subject.kind(),
+                promotion.subject.id(),
+                environment.name
+            );
+            return Ok(promotion);
+        }
+        self.apply(environment, &mut promotion).await?;
+        Ok(promotion)
+    }
+
+    // Records the outcome either way; a failed apply is returned as the error
+    async fn apply(&self, environment: &Environment, promotion: &mut Promotion) -> AutomationResult<()> {
+        let result = self.target(&promotion.subject)?.apply(environment, &promotion.subject, &promotion.rendered).await;
+        match &result {
+            Ok(()) => {
+                promotion.status = PromotionStatus::Applied;
+                promotion.applied_at = Some(Utc::now());
+                info!(
+                    "Applied {} {} version {} to {}",
+                    promotion.subject.kind(),
+                    promotion.subject.id(),
+                    promotion.release.version,
+                    environment.name
+                );
+            }
+            Err(e) => {
+                promotion.status = PromotionStatus::Failed { error: e.to_string() };
+                warn!("Promotion {} into {} failed: {}", promotion.id, environment.name, e);
+            }
+        }
+        self.store.save_promotion(promotion).await?;
+        result
+    }
+
+    // Applies a promotion waiting on a gated environment. The config is applied as it was
+    // rendered when promoted, which is what the approver reviewed.
+    pub async fn approve(&self, project_id: &str, promotion_id: &str, approved_by: &str) -> AutomationResult<Promotion> {
+        let _guard = self.lock.lock().await;
+        let mut promotion = self.get_promotion(project_id, promotion_id).await?;
+        if promotion.status != PromotionStatus::AwaitingApproval {
+            return Err(AutomationError::Conflict(format!("Promotion {} is not waiting for approval", promotion_id)));
+        }
+        if promotion.promoted_by == approved_by {
+            return Err(AutomationError::Auth(format!("Promotion {} can't be approved by the one who promoted it", promotion_id)));
+        }
+        let environment = self.get_environment(project_id, &promotion.environment).await?;
+        promotion.approval = Some(PromotionApproval { approved_by: approved_by.to_string(), approved_at: Utc::now() });
+        info!("Promotion {} into {} approved by {}", promotion_id, environment.name, approved_by);
+        self.apply(&environment, &mut promotion).await?;
+        Ok(promotion)
+    }
+
+    pub async fn reject(&self, project_id: &str, promotion_id: &str, rejected_by: &str, reason: &str) -> AutomationResult<Promotion> {
+        let _guard = self.lock.lock().await;
+        let mut promotion = self.get_promotion(project_id, promotion_id).await?;
+        if promotion.status != PromotionStatus::AwaitingApproval {
+            return Err(AutomationError::Conflict(format!("Promotion {} is not waiting for approval", promotion_id)));
+        }
+        promotion.status = PromotionStatus::Rejected { rejected_by: rejected_by.to_string(), reason: reason.to_string() };
+        self.store.save_promotion(&promotion).await?;
+        Ok(promotion)
+    }
+
+    // Compares each subject's live config in the environment with its last applied promotion
+    pub async fn detect_drift(&self, project_id: &str, environment: &str) -> AutomationResult<Vec<DriftReport>> {
+        let environment = self.get_environment(project_id, environment).await?;
+        let mut current: BTreeMap<u64, Promotion> = BTreeMap::new();
+        let mut latest: HashMap<PromotionSubject, u64> = HashMap::new();
+        for promotion in self.store.list_promotions(project_id).await? {
+            if promotion.environment != environment.name || promotion.status != PromotionStatus::Applied {
+                continue;
+            }
+            if let Some(earlier) = latest.insert(promotion.subject.clone(), promotion.sequence) {
+                current.remove(&earlier);
+            }
+            current.insert(promotion.sequence, promotion);
+        }
+
+        let mut reports = Vec::new();
+        for promotion in current.into_values() {
+            let live = self.target(&promotion.subject)?.live_config(&environment, &promotion.subject).await?;
+            let changes = diff_configs(Some(&promotion.rendered), live.as_ref());
+            if !changes.is_empty() {
+                reports.push(DriftReport {
+                    subject: promotion.subject,
+                    promotion_id: promotion.id,
+                    version: promotion.release.version,
+                    changes,
+                });
+            }
+        }
+        Ok(reports)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::artifact::LocalObjectStore;
+    use serde_json::json;
+    use std::sync::Mutex as StdMutex;
+
+    #[derive(Default)]
+    struct Deployed {
+        live: StdMutex<HashMap<(String, PromotionSubject), Value>>,
+    }
+
+    impl Deployed {
+        fn live(&self, environment: &str, subject: &PromotionSubject) -> Option<Value> {
+            self.live.lock().unwrap().get(&(environment.to_string(), subject.clone())).cloned()
+        }
+    }
+
+    #[async_trait]
+    impl ConfigTarget for Deployed {
+        async fn apply(&self, environment: &Environment, subject: &PromotionSubject, config: &Value) -> AutomationResult<()> {
+            self.live.lock().unwrap().insert((environment.name.clone(), subject.clone()), config.clone());
+            Ok(())
+        }
+
+        async fn live_config(&self, environment: &Environment, subject: &PromotionSubject) -> AutomationResult<Option<Value>> {
+            Ok(self.live(&environment.name, subject))
+        }
+    }
+
+    async fn pipeline(dir: &std::path::Path) -> (PromotionPipeline, Arc<Deployed>) {
+        let deployed = Arc::new(Deployed::default());
+        let store = Arc::new(ObjectEnvironmentStore::new(Arc::new(LocalObjectStore::new(dir))));
+        let pipeline = PromotionPipeline::new(store)
+            .with_target("workflow", deployed.clone())
+            .with_target("ml_deployment", deployed.clone());
+        let environments = [
+            Environment::new("shop", "dev").with_variable("db_host", "db.dev").with_variable("replicas", 1).with_promotes_to("staging"),
+            Environment::new("shop", "staging").with_variable("db_host", "db.staging").with_variable("replicas", 2).with_promotes_to("prod"),
+            Environment::new("shop", "prod").with_variable("db_host", "db.prod").with_variable("replicas", 6).with_approval(),
+        ];
+        for environment in environments {
+            pipeline.create_environment(environment).await.unwrap();
+        }
+        (pipeline, deployed)
+    }
+
+    fn release(version: &str, image: &str) -> Release {
+        Release::new(
+            version,
+            json!({
+                "image": image,
+                "instance_count": "{{ env.replicas }}",
+                "environment": {"DATABASE_URL": "postgres://{{ env.db_host }}:5432/shop", "RUN_ID": "{{ run.id }}"},
+            }),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_promotion_substitutes_variables_and_gates_prod() {
+        let dir = tempfile::tempdir().unwrap();
+        let (pipeline, deployed) = pipeline(dir.path()).await;
+        let model = PromotionSubject::MlDeployment { deployment_id: "ranker".into() };
+
+        pipeline.release("shop", "dev", model.clone(), release("1", "ranker:1"), "dev@sirsi").await.unwrap();
+        let staged = pipeline.promote("shop", "dev", model.clone(), "dev@sirsi").await.unwrap();
+        assert_eq!(staged.source_environment.as_deref(), Some("dev"));
+        assert_eq!(
+            deployed.live("staging", &model).unwrap(),
+            json!({
+                "image": "ranker:1",
+                "instance_count": 2,
+                "environment": {"DATABASE_URL": "postgres://db.staging:5432/shop", "RUN_ID": "{{ run.id }}"},
+            })
+        );
+        assert_eq!(deployed.live("dev", &model).unwrap()["instance_count"], 1);
+
+        let gated = pipeline.promote("shop", "staging", model.clone(), "dev@sirsi").await.unwrap();
+        assert_eq!(gated.status, PromotionStatus::AwaitingApproval);
+        assert!(deployed.live("prod", &model).is_none());
+        assert!(gated.changes.iter().any(|c| c.path == "/" && c.from.is_none()));
+        assert!(matches!(pipeline.promote("shop", "staging", model.clone(), "dev@sirsi").await, Err(AutomationError::Conflict(_))));
+        assert!(matches!(pipeline.approve("shop", &gated.id, "dev@sirsi").await, Err(AutomationError::Auth(_))));
+
+        let approved = pipeline.approve("shop", &gated.id, "lead@sirsi").await.unwrap();
+        assert_eq!(approved.status, PromotionStatus::Applied);
+        assert_eq!(approved.approval.unwrap().approved_by, "lead@sirsi");
+        assert_eq!(deployed.live("prod", &model).unwrap()["environment"]["DATABASE_URL"], "postgres://db.prod:5432/shop");
+        assert!(pipeline.promote("shop", "prod", model.clone(), "lead@sirsi").await.is_err());
+
+        // A variable staging doesn't define stops the promotion before anything is recorded
+        let mut broken = release("2", "ranker:2");
+        broken.config["environment"]["CACHE"] = json!("{{ env.cache_host }}");
+        let dev = pipeline.get_environment("shop", "dev").await.unwrap().with_variable("cache_host", "cache.dev");
+        pipeline.update_environment(dev).await.unwrap();
+        pipeline.release("shop", "dev", model.clone(), broken, "dev@sirsi").await.unwrap();
+        let err = pipeline.promote("shop", "dev", model.clone(), "dev@sirsi").await.unwrap_err();
+        assert!(err.to_string().contains("Environment staging is missing variables: cache_host"));
+        assert_eq!(pipeline.history("shop", "staging", &model).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_rollback_and_drift() {
+        let dir = tempfile::tempdir().unwrap();
+        let (pipeline, deployed) = pipeline(dir.path()).await;
+        let workflow = PromotionSubject::Workflow { workflow_id: "nightly-etl".into() };
+
+        let first = pipeline.release("shop", "dev", workflow.clone(), release("1", "etl:1"), "dev@sirsi").await.unwrap();
+        let second = pipeline.release("shop", "dev", workflow.clone(), release("2", "etl:2"), "dev@sirsi").await.unwrap();
+        assert_eq!(
+            second.changes,
+            vec![ConfigChange { path: "/image".into(), from: Some(json!("etl:1")), to: Some(json!("etl:2")) }]
+        );
+
+        // Rolled back to the config version 1 was applied with, even after variables change
+        let dev = pipeline.get_environment("shop", "dev").await.unwrap().with_variable("replicas", 3);
+        pipeline.update_environment(dev).await.unwrap();
+        let rollback = pipeline.rollback("shop", "dev", workflow.clone(), "oncall@sirsi").await.unwrap();
+        assert_eq!(rollback.restores.as_deref(), Some(first.id.as_str()));
+        assert_eq!(rollback.release.version, "1");
+        assert_eq!(deployed.live("dev", &workflow).unwrap(), first.rendered);
+        let current = pipeline.current("shop", "dev", &workflow).await.unwrap().unwrap();
+        assert_eq!(current.id, rollback.id);
+        // Rolling back again goes to version 2, the last one that isn't running
+        let again = pipeline.rollback("shop", "dev", workflow.clone(), "oncall@sirsi").await.unwrap();
+        assert_eq!(again.restores.as_deref(), Some(second.id.as_str()));
+
+        assert!(pipeline.detect_drift("shop", "dev").await.unwrap().is_empty());
+        let mut live = deployed.live("dev", &workflow).unwrap();
+        live["instance_count"] = json!(9);
+        deployed.live.lock().unwrap().insert(("dev".into(), workflow.clone()), live);
+        let drift = pipeline.detect_drift("shop", "dev").await.unwrap();
+        assert_eq!(drift.len(), 1);
+        assert_eq!((drift[0].promotion_id.as_str(), drift[0].version.as_str()), (again.id.as_str(), "2"));
+        assert_eq!(
+            drift[0].changes,
+            vec![ConfigChange { path: "/instance_count".into(), from: Some(json!(1)), to: Some(json!(9)) }]
+        );
+    }
+}
@@ -0,0 +1,124 @@
+use sirsi_common::{ErrorKind, Retryable};
+use sirsi_compute_manager::ComputeError;
+use sirsi_data_services::DataError;
+use sirsi_network_services::NetworkError;
+use sirsi_observability::ObservabilityError;
+use thiserror::Error;
+use tonic::Status;
+
+#[derive(Error, Debug)]
+pub enum AutomationError {
+    #[error("Workflow error: {0}")]
+    Workflow(String),
+
+    #[error("Task error: {0}")]
+    Task(String),
+
+    #[error("Trigger error: {0}")]
+    Trigger(String),
+
+    #[error("Notification error: {0}")]
+    Notification(String),
+
+    #[error("Data service error: {0}")]
+    Data(#[from] DataError),
+
+    #[error("Observability error: {0}")]
+    Observability(#[from] ObservabilityError),
+
+    #[error("Network service error: {0}")]
+    Network(#[from] NetworkError),
+
+    #[error("Compute error: {0}")]
+    Compute(#[from] ComputeError),
+
+    #[error("Request throttled: {0}")]
+    Throttled(String),
+
+    #[error("Service unavailable: {0}")]
+    Unavailable(String),
+
+    #[error("Authentication error: {0}")]
+    Auth(String),
+
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
+    #[error("Validation error: {0}")]
+    Validation(String),
+
+    #[error("Resource not found: {0}")]
+    NotFound(String),
+
+    #[error("Configuration error: {0}")]
+    Config(String),
+
+    #[error("Internal error: {0}")]
+    Internal(String),
+}
+
+impl AutomationError {
+    pub fn from_kind(kind: ErrorKind, msg: impl Into<String>) -> Self {
+        let msg = msg.into();
+        match kind {
+            ErrorKind::Throttled => AutomationError::Throttled(msg),
+            ErrorKind::ProviderOutage => AutomationError::Unavailable(msg),
+            ErrorKind::AuthFailure => AutomationError::Auth(msg),
+            ErrorKind::NotFound => AutomationError::NotFound(msg),
+            ErrorKind::Conflict => AutomationError::Conflict(msg),
+            ErrorKind::InvalidInput => AutomationError::Validation(msg),
+            ErrorKind::Internal => AutomationError::Internal(msg),
+        }
+    }
+
+    pub fn from_http_status(status: u16, msg: impl Into<String>) -> Self {
+        Self::from_kind(ErrorKind::from_http_status(status), msg)
+    }
+}
+
+impl Retryable for AutomationError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            AutomationError::Data(e) => e.kind(),
+            AutomationError::Observability(e) => e.kind(),
+            AutomationError::Network(e) => e.kind(),
+            AutomationError::Compute(e) => e.kind(),
+            AutomationError::Throttled(_) => ErrorKind::Throttled,
+            AutomationError::Unavailable(_) => ErrorKind::ProviderOutage,
+            AutomationError::Auth(_) => ErrorKind::AuthFailure,
+            AutomationError::Conflict(_) => ErrorKind::Conflict,
+            AutomationError::Validation(_) | AutomationError::Config(_) => ErrorKind::InvalidInput,
+            AutomationError::NotFound(_) => ErrorKind::NotFound,
+            AutomationError::Workflow(_)
+            | AutomationError::Task(_)
+            | AutomationError::Trigger(_)
+            | AutomationError::Notification(_)
+            | AutomationError::Internal(_) => ErrorKind::Internal,
+        }
+    }
+}
+
+impl From<AutomationError> for Status {
+    fn from(error: AutomationError) -> Self {
+        match error {
+            AutomationError::Workflow(msg) => Status::internal(msg),
+            AutomationError::Task(msg) => Status::internal(msg),
+            AutomationError::Trigger(msg) => Status::internal(msg),
+            AutomationError::Notification(msg) => Status::internal(msg),
+            AutomationError::Data(e) => e.into(),
+            AutomationError::Observability(e) => e.into(),
+            AutomationError::Network(e) => e.into(),
+            AutomationError::Compute(e) => e.into(),
+            AutomationError::Throttled(msg) => Status::resource_exhausted(msg),
+            AutomationError::Unavailable(msg) => Status::unavailable(msg),
+            AutomationError::Auth(msg) => Status::unauthenticated(msg),
+            AutomationError::Conflict(msg) => Status::aborted(msg),
+            AutomationError::Validation(msg) => Status::invalid_argument(msg),
+            AutomationError::NotFound(msg) => Status::not_found(msg),
+            AutomationError::Config(msg) => Status::failed_precondition(msg),
+            AutomationError::Internal(msg) => Status::internal(msg),
+        }
+    }
+}
+
+pub type AutomationResult<T> = Result<T, AutomationError>;
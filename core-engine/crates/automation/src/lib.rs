@@ -0,0 +1,11 @@
+pub mod artifact;
+pub mod declarative;
+pub mod environment;
+pub mod error;
+pub mod metrics;
+pub mod migration;
+pub mod notification;
+pub mod trigger;
+pub mod workflow;
+
+pub use error::{AutomationError, AutomationResult};
@@ -0,0 +1,563 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use sirsi_observability::monitoring::{
+    AggregationType, DashboardDefinition, DashboardVariable, DashboardWidget, MetricDataPoint, MetricDefinition,
+    MetricQuery, MetricType, MetricUnit, MetricValue, MetricsManager, VariableType, WidgetPosition, WidgetType,
+};
+use tokio::sync::{watch, Mutex};
+use tracing::{debug, warn};
+
+use crate::error::AutomationResult;
+use crate::workflow::{RunStatus, TaskRun, WorkflowRun};
+
+pub const NAMESPACE: &str = "SirsiNexus/Automation";
+
+// Started/completed/succeeded/failed counters let alert rules express failure rate as failed / completed
+pub const RUNS_STARTED: &str = "workflow_runs_started";
+pub const RUNS_COMPLETED: &str = "workflow_runs_completed";
+pub const RUNS_SUCCEEDED: &str = "workflow_runs_succeeded";
+pub const RUNS_FAILED: &str = "workflow_runs_failed";
+pub const RUN_DURATION: &str = "workflow_run_duration_seconds";
+pub const RUN_FAILED_TASKS: &str = "workflow_run_failed_tasks";
+pub const RUN_RETRIED_TASKS: &str = "workflow_run_retried_tasks";
+pub const TASK_DURATION: &str = "task_duration_seconds";
+pub const TASK_RETRIES: &str = "task_retries";
+pub const TASK_FAILURES: &str = "task_failures";
+pub const TASK_CPU_SECONDS: &str = "task_cpu_seconds";
+pub const TASK_MEMORY_MB_SECONDS: &str = "task_memory_mb_seconds";
+pub const TASK_IO_BYTES: &str = "task_io_bytes";
+
+pub const DIM_WORKFLOW_ID: &str = "workflow_id";
+pub const DIM_TASK_ID: &str = "task_id";
+pub const DIM_STATUS: &str = "status";
+
+pub fn status_label(status: &RunStatus) -> &'static str {
+    match status {
+        RunStatus::Pending => "pending",
+        RunStatus::Running => "running",
+        RunStatus::Succeeded => "succeeded",
+        RunStatus::Failed => "failed",
+        RunStatus::Cancelled => "cancelled",
+        RunStatus::TimedOut => "timed_out",
+    }
+}
+
+fn definition(name: &str, metric_type: MetricType, unit: MetricUnit, dimensions: &[&str]) -> MetricDefinition {
+    let aggregations = match metric_type {
+        MetricType::Counter => vec![AggregationType::Sum],
+        _ => vec![
+            AggregationType::Average,
+            AggregationType::Maximum,
+            AggregationType::Percentile(95.0),
+        ],
+    };
+    MetricDefinition {
+        name: name.to_string(),
+        namespace: NAMESPACE.to_string(),
+        metric_type,
+        unit,
+        dimensions: dimensions.iter().map(|d| d.to_string()).collect(),
+        aggregations,
+        retention_days: 30,
+    }
+}
+
+pub fn metric_definitions() -> Vec<MetricDefinition> {
+    let run = &[DIM_WORKFLOW_ID, DIM_STATUS];
+    let task = &[DIM_WORKFLOW_ID, DIM_TASK_ID, DIM_STATUS];
+    vec![
+        definition(RUNS_STARTED, MetricType::Counter, MetricUnit::Count, &[DIM_WORKFLOW_ID]),
+        definition(RUNS_COMPLETED, MetricType::Counter, MetricUnit::Count, run),
+        definition(RUNS_SUCCEEDED, MetricType::Counter, MetricUnit::Count, run),
+        definition(RUNS_FAILED, MetricType::Counter, MetricUnit::Count, run),
+        definition(RUN_DURATION, MetricType::Histogram, MetricUnit::Seconds, run),
+        definition(RUN_FAILED_TASKS, MetricType::Gauge, MetricUnit::Count, run),
+        definition(RUN_RETRIED_TASKS, MetricType::Gauge, MetricUnit::Count, run),
+        definition(TASK_DURATION, MetricType::Histogram, MetricUnit::Seconds, task),
+        definition(TASK_RETRIES, MetricType::Counter, MetricUnit::Count, task),
+        definition(TASK_FAILURES, MetricType::Counter, MetricUnit::Count, task),
+        definition(TASK_CPU_SECONDS, MetricType::Gauge, MetricUnit::Seconds, task),
+        definition(TASK_MEMORY_MB_SECONDS, MetricType::Gauge, MetricUnit::None, task),
+        definition(TASK_IO_BYTES, MetricType::Gauge, MetricUnit::Bytes, task),
+    ]
+}
+
+fn point(name: &str, dimensions: &HashMap<String, String>, timestamp: DateTime<Utc>, value: f64) -> MetricDataPoint {
+    MetricDataPoint {
+        name: name.to_string(),
+        namespace: NAMESPACE.to_string(),
+        dimensions: dimensions.clone(),
+        timestamp,
+        value: MetricValue::Single(value),
+        exemplars: Vec::new(),
+    }
+}
+
+pub fn run_started_points(workflow_id: &str, timestamp: DateTime<Utc>) -> Vec<MetricDataPoint> {
+    let dimensions = HashMap::from([(DIM_WORKFLOW_ID.to_string(), workflow_id.to_string())]);
+    vec![point(RUNS_STARTED, &dimensions, timestamp, 1.0)]
+}
+
+pub fn run_completed_points(run: &WorkflowRun) -> Vec<MetricDataPoint> {
+    let timestamp = run.end_time.unwrap_or_else(Utc::now);
+    let dimensions = HashMap::from([
+        (DIM_WORKFLOW_ID.to_string(), run.workflow_id.clone()),
+        (DIM_STATUS.to_string(), status_label(&run.status).to_string()),
+    ]);
+    let succeeded = matches!(run.status, RunStatus::Succeeded);
+    let failed = matches!(run.status, RunStatus::Failed | RunStatus::TimedOut);
+
+    let mut points = vec![
+        point(RUNS_COMPLETED, &dimensions, timestamp, 1.0),
+        point(RUNS_SUCCEEDED, &dimensions, timestamp, if succeeded { 1.0 } else { 0.0 }),
+        point(RUNS_FAILED, &dimensions, timestamp, if failed { 1.0 } else { 0.0 }),
+        point(RUN_DURATION, &dimensions, timestamp, run.metrics.total_duration_seconds as f64),
+        point(RUN_FAILED_TASKS, &dimensions, timestamp, run.metrics.failed_tasks as f64),
+        point(RUN_RETRIED_TASKS, &dimensions, timestamp, run.metrics.retried_tasks as f64),
+    ];
+    for task_run in &run.task_runs {
+        points.extend(task_points(&run.workflow_id, task_run, timestamp));
+    }
+    points
+}
+
+fn task_points(workflow_id: &str, task_run: &TaskRun, run_end: DateTime<Utc>) -> Vec<MetricDataPoint> {
+    let timestamp = task_run.end_time.unwrap_or(run_end);
+    let dimensions = HashMap::from([
+        (DIM_WORKFLOW_ID.to_string(), workflow_id.to_string()),
+        (DIM_TASK_ID.to_string(), task_run.task_id.clone()),
+        (DIM_STATUS.to_string(), status_label(&task_run.status).to_string()),
+    ]);
+    let metrics = &task_run.metrics;
+    let failed = matches!(task_run.status, RunStatus::Failed | RunStatus::TimedOut);
+    vec![
+        point(TASK_DURATION, &dimensions, timestamp, metrics.duration_seconds as f64),
+        point(TASK_RETRIES, &dimensions, timestamp, metrics.retry_count as f64),
+        point(TASK_FAILURES, &dimensions, timestamp, if failed { 1.0 } else { 0.0 }),
+        point(TASK_CPU_SECONDS, &dimensions, timestamp, metrics.resource_usage.cpu_seconds),
+        point(TASK_MEMORY_MB_SECONDS, &dimensions, timestamp, metrics.resource_usage.memory_mb_seconds),
+        point(TASK_IO_BYTES, &dimensions, timestamp, metrics.resource_usage.io_bytes as f64),
+    ]
+}
+
+#[derive(Debug, Clone)]
+pub struct ExporterConfig {
+    pub buffer_capacity: usize,
+    pub batch_size: usize,
+    pub flush_interval: Duration,
+}
+
+impl Default for ExporterConfig {
+    fn default() -> Self {
+        Self {
+            buffer_capacity: 10_000,
+            batch_size: 500,
+            flush_interval: Duration::from_secs(15),
+        }
+    }
+}
+
+// Buffers points between flushes; when the backend is down the buffer fills and the oldest points are dropped
+pub struct MetricsExporter {
+    manager: Arc<dyn MetricsManager>,
+    config: ExporterConfig,
+    buffer: Mutex<VecDeque<MetricDataPoint>>,
+    dropped: AtomicU64,
+}
+
+impl MetricsExporter {
+    pub fn new(manager: Arc<dyn MetricsManager>, config: ExporterConfig) -> Self {
+        Self {
+            manager,
+            config,
+            buffer: Mutex::new(VecDeque::new()),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    pub async fn register_metrics(&self) -> AutomationResult<()> {
+        for definition in metric_definitions() {
+            self.manager.register_metric(definition).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn record_run_started(&self, workflow_id: &str) {
+        self.enqueue(run_started_points(workflow_id, Utc::now())).await;
+    }
+
+    pub async fn record_run_completed(&self, run: &WorkflowRun) {
+        self.enqueue(run_completed_points(run)).await;
+    }
+
+    pub fn dropped_points(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    pub async fn buffered_points(&self) -> usize {
+        self.buffer.lock().await.len()
+    }
+
+    async fn enqueue(&self, points: Vec<MetricDataPoint>) {
+        let mut buffer = self.buffer.lock().await;
+        buffer.extend(points);
+        self.trim(&mut buffer);
+    }
+
+    fn trim(&self, buffer: &mut VecDeque<MetricDataPoint>) {
+        let overflow = buffer.len().saturating_sub(self.config.buffer_capacity);
+        if overflow > 0 {
+            buffer.drain(..overflow);
+            self.dropped.fetch_add(overflow as u64, Ordering::Relaxed);
+            warn!("Metrics buffer full, dropped {} points", overflow);
+        }
+    }
+
+    // Sends buffered points in batches; a failed batch is returned to the front of the buffer
+    pub async fn flush(&self) -> AutomationResult<usize> {
+        let mut sent = 0;
+        loop {
+            let batch: Vec<MetricDataPoint> = {
+                let mut buffer = self.buffer.lock().await;
+                let size = buffer.len().min(self.config.batch_size);
+                buffer.drain(..size).collect()
+            };
+            if batch.is_empty() {
+                return Ok(sent);
+            }
+
+            let size = batch.len();
+            if let Err(e) = self.manager.put_metric_data(batch.clone()).await {
+                let mut buffer = self.buffer.lock().await;
+                for point in batch.into_iter().rev() {
+                    buffer.push_front(point);
+                }
+                self.trim(&mut buffer);
+                return Err(e.into());
+            }
+            sent += size;
+            debug!("Exported {} workflow metric points", size);
+        }
+    }
+
+    pub fn spawn(self: Arc<Self>, mut shutdown: watch::Receiver<bool>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.config.flush_interval);
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {}
+                    _ = shutdown.changed() => {
+                        let _ = self.flush().await;
+                        return;
+                    }
+                }
+                if let Err(e) = self.flush().await {
+                    warn!("Failed to export workflow metrics: {}", e);
+                }
+            }
+        })
+    }
+}
+
+fn query(name: &str, aggregation: AggregationType, dimensions: &HashMap<String, String>) -> MetricQuery {
+    let end_time = Utc::now();
+    MetricQuery {
+        metric_name: name.to_string(),
+        namespace: NAMESPACE.to_string(),
+        dimensions: Some(dimensions.clone()),
+        aggregation,
+        period: 300,
+        start_time: end_time - chrono::Duration::hours(24),
+        end_time,
+        include_exemplars: false,
+    }
+}
+
+fn widget(
+    id: &str,
+    title: &str,
+    widget_type: WidgetType,
+    metrics: Vec<MetricQuery>,
+    (x, y, width, height): (i32, i32, i32, i32),
+) -> DashboardWidget {
+    DashboardWidget {
+        id: id.to_string(),
+        title: title.to_string(),
+        widget_type,
+        metrics,
+        position: WidgetPosition { x, y, width, height },
+        properties: HashMap::new(),
+    }
+}
+
+// Default workflow-health dashboard; dimension values use the `$workflow_id` dashboard variable
+pub fn workflow_health_dashboard() -> DashboardDefinition {
+    let by_workflow = HashMap::from([(DIM_WORKFLOW_ID.to_string(), "$workflow_id".to_string())]);
+    DashboardDefinition {
+        id: "workflow-health".to_string(),
+        name: "Workflow Health".to_string(),
+        description: "Run outcomes, durations and task failures for automation workflows".to_string(),
+        widgets: vec![
+            widget(
+                "runs",
+                "Runs",
+                WidgetType::LineGraph,
+                vec![
+                    query(RUNS_STARTED, AggregationType::Sum, &by_workflow),
+                    query(RUNS_SUCCEEDED, AggregationType::Sum, &by_workflow),
+                    query(RUNS_FAILED, AggregationType::Sum, &by_workflow),
+                ],
+                (0, 0, 8, 4),
+            ),
+            widget(
+                "failed-runs",
+                "Failed runs (24h)",
+                WidgetType::SingleValue,
+                vec![query(RUNS_FAILED, AggregationType::Sum, &by_workflow)],
+                (8, 0, 4, 4),
+            ),
+            widget(
+                "run-duration",
+                "Run duration",
+                WidgetType::LineGraph,
+                vec![
+                    query(RUN_DURATION, AggregationType::Average, &by_workflow),
+                    query(RUN_DURATION, AggregationType::Percentile(95.0), &by_workflow),
+                ],
+                (0, 4, 6, 4),
+            ),
+            widget(
+                "task-failures",
+                "Task failures",
+                WidgetType::BarGraph,
+                vec![query(TASK_FAILURES, AggregationType::Sum, &by_workflow)],
+                (6, 4, 6, 4),
+            ),
+            widget(
+                "task-retries",
+                "Task retries",
+                WidgetType::BarGraph,
+                vec![query(TASK_RETRIES, AggregationType::Sum, &by_workflow)],
+                (0, 8, 6, 4),
+            ),
+            widget(
+                "task-duration",
+                "Task duration (p95)",
+                WidgetType::Table,
+                vec![query(TASK_DURATION, AggregationType::Percentile(95.0), &by_workflow)],
+                (6, 8, 6, 4),
+            ),
+        ],
+        variables: vec![DashboardVariable {
+            name: "workflow_id".to_string(),
+            label: "Workflow".to_string(),
+            type_: VariableType::Query {
+                query: format!("dimension_values({}, {})", NAMESPACE, DIM_WORKFLOW_ID),
+            },
+            default_value: Some("*".to_string()),
+            multi_value: true,
+        }],
+        refresh_interval: 60,
+        tags: HashMap::from([("component".to_string(), "automation".to_string())]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workflow::{ResourceUsage, RunMetrics, RunTrigger, TaskMetrics, TriggerType};
+    use async_trait::async_trait;
+    use sirsi_observability::{ObservabilityError, ObservabilityResult};
+    use std::sync::atomic::AtomicBool;
+
+    #[derive(Default)]
+    struct RecordingMetrics {
+        batches: Mutex<Vec<Vec<MetricDataPoint>>>,
+        down: AtomicBool,
+    }
+
+    #[async_trait]
+    impl MetricsManager for RecordingMetrics {
+        async fn register_metric(&self, _definition: MetricDefinition) -> ObservabilityResult<()> {
+            Ok(())
+        }
+
+        async fn put_metric_data(&self, data_points: Vec<MetricDataPoint>) -> ObservabilityResult<()> {
+            if self.down.load(Ordering::SeqCst) {
+                return Err(ObservabilityError::Unavailable("metrics backend down".into()));
+            }
+            self.batches.lock().await.push(data_points);
+            Ok(())
+        }
+
+        async fn get_metric_data(&self, _query: MetricQuery) -> ObservabilityResult<Vec<MetricDataPoint>> {
+            Ok(Vec::new())
+        }
+
+        async fn list_metrics(&self, _namespace: Option<String>) -> ObservabilityResult<Vec<MetricDefinition>> {
+            Ok(Vec::new())
+        }
+
+        async fn delete_metric(&self, _name: &str, _namespace: &str) -> ObservabilityResult<()> {
+            Ok(())
+        }
+    }
+
+    fn task_run(task_id: &str, status: RunStatus, duration: i64, retries: i32) -> TaskRun {
+        TaskRun {
+            id: format!("run-1-{}", task_id),
+            task_id: task_id.to_string(),
+            status,
+            start_time: Utc::now(),
+            end_time: Some(Utc::now()),
+            inputs: HashMap::new(),
+            outputs: HashMap::new(),
+            error: None,
+            logs_uri: None,
+            metrics: TaskMetrics {
+                duration_seconds: duration,
+                retry_count: retries,
+                resource_usage: ResourceUsage {
+                    cpu_seconds: 1.5,
+                    memory_mb_seconds: 256.0,
+                    io_bytes: 2048,
+                },
+            },
+        }
+    }
+
+    fn synthetic_run() -> WorkflowRun {
+        WorkflowRun {
+            id: "run-1".into(),
+            workflow_id: "nightly-etl".into(),
+            version: "3".into(),
+            status: RunStatus::Failed,
+            trigger: RunTrigger {
+                type_: TriggerType::Cron,
+                source: "scheduler".into(),
+                event: None,
+            },
+            task_runs: vec![
+                task_run("extract", RunStatus::Succeeded, 40, 0),
+                task_run("load", RunStatus::Failed, 20, 2),
+            ],
+            variables: HashMap::new(),
+            start_time: Utc::now(),
+            end_time: Some(Utc::now()),
+            metrics: RunMetrics {
+                total_duration_seconds: 60,
+                task_count: 2,
+                failed_tasks: 1,
+                retried_tasks: 1,
+                resource_usage: ResourceUsage {
+                    cpu_seconds: 3.0,
+                    memory_mb_seconds: 512.0,
+                    io_bytes: 4096,
+                },
+            },
+        }
+    }
+
+    fn value_of(points: &[MetricDataPoint], name: &str, task_id: Option<&str>) -> f64 {
+        let point = points
+            .iter()
+            .find(|p| p.name == name && p.dimensions.get(DIM_TASK_ID).map(String::as_str) == task_id)
+            .unwrap_or_else(|| panic!("missing point {} for {:?}", name, task_id));
+        match point.value {
+            MetricValue::Single(v) => v,
+            _ => panic!("unexpected value for {}", name),
+        }
+    }
+
+    #[test]
+    fn test_run_completed_points() {
+        let points = run_completed_points(&synthetic_run());
+        assert_eq!(points.len(), 6 + 2 * 6);
+        assert!(points.iter().all(|p| p.namespace == NAMESPACE));
+        assert!(points.iter().all(|p| p.dimensions[DIM_WORKFLOW_ID] == "nightly-etl"));
+
+        assert_eq!(value_of(&points, RUNS_COMPLETED, None), 1.0);
+        assert_eq!(value_of(&points, RUNS_FAILED, None), 1.0);
+        assert_eq!(value_of(&points, RUNS_SUCCEEDED, None), 0.0);
+        assert_eq!(value_of(&points, RUN_DURATION, None), 60.0);
+        assert_eq!(value_of(&points, RUN_FAILED_TASKS, None), 1.0);
+
+        assert_eq!(value_of(&points, TASK_DURATION, Some("extract")), 40.0);
+        assert_eq!(value_of(&points, TASK_FAILURES, Some("extract")), 0.0);
+        assert_eq!(value_of(&points, TASK_FAILURES, Some("load")), 1.0);
+        assert_eq!(value_of(&points, TASK_RETRIES, Some("load")), 2.0);
+        assert_eq!(value_of(&points, TASK_IO_BYTES, Some("load")), 2048.0);
+
+        let load = points.iter().find(|p| p.dimensions.get(DIM_TASK_ID).map(String::as_str) == Some("load")).unwrap();
+        assert_eq!(load.dimensions[DIM_STATUS], "failed");
+        let run = points.iter().find(|p| p.name == RUNS_COMPLETED).unwrap();
+        assert_eq!(run.dimensions[DIM_STATUS], "failed");
+    }
+
+    #[tokio::test]
+    async fn test_flush_batches_points() {
+        let backend = Arc::new(RecordingMetrics::default());
+        let exporter = MetricsExporter::new(
+            backend.clone(),
+            ExporterConfig {
+                batch_size: 5,
+                ..Default::default()
+            },
+        );
+
+        exporter.record_run_started("nightly-etl").await;
+        exporter.record_run_completed(&synthetic_run()).await;
+        assert_eq!(exporter.flush().await.unwrap(), 19);
+
+        let batches = backend.batches.lock().await;
+        assert_eq!(batches.iter().map(Vec::len).collect::<Vec<_>>(), vec![5, 5, 5, 4]);
+        assert_eq!(batches[0][0].name, RUNS_STARTED);
+        assert_eq!(exporter.buffered_points().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_buffer_is_bounded_while_backend_is_down() {
+        let backend = Arc::new(RecordingMetrics::default());
+        backend.down.store(true, Ordering::SeqCst);
+        let exporter = MetricsExporter::new(
+            backend.clone(),
+            ExporterConfig {
+                buffer_capacity: 20,
+                batch_size: 10,
+                ..Default::default()
+            },
+        );
+
+        exporter.record_run_completed(&synthetic_run()).await;
+        assert!(exporter.flush().await.is_err());
+        assert_eq!(exporter.buffered_points().await, 18);
+        assert_eq!(exporter.dropped_points(), 0);
+
+        exporter.record_run_completed(&synthetic_run()).await;
+        assert_eq!(exporter.buffered_points().await, 20);
+        assert_eq!(exporter.dropped_points(), 16);
+
+        backend.down.store(false, Ordering::SeqCst);
+        assert_eq!(exporter.flush().await.unwrap(), 20);
+    }
+
+    #[test]
+    fn test_workflow_health_dashboard() {
+        let dashboard = workflow_health_dashboard();
+        assert_eq!(dashboard.id, "workflow-health");
+        assert_eq!(dashboard.variables[0].name, "workflow_id");
+
+        let known: Vec<String> = metric_definitions().into_iter().map(|d| d.name).collect();
+        for widget in &dashboard.widgets {
+            assert!(!widget.metrics.is_empty());
+            for query in &widget.metrics {
+                assert!(known.contains(&query.metric_name), "{}", query.metric_name);
+                assert_eq!(query.dimensions.as_ref().unwrap()[DIM_WORKFLOW_ID], "$workflow_id");
+            }
+        }
+    }
+}
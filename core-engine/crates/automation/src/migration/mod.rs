@@ -0,0 +1,703 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex as StdMutex};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::{info, warn};
+
+use crate::artifact::ObjectStore;
+use crate::error::{AutomationError, AutomationResult};
+
+pub mod steps;
+
+pub use steps::{ComputeProvisioner, CreateTargetResource, CutoverDns, DecommissionSource, ProvisionedTarget, SyncData};
+
+const EXECUTION_PREFIX: &str = "migrations/executions/";
+
+// A migration plan as produced by the planner: waves run in order, steps within a wave run
+// one after another so a failure leaves a well-defined set of completed steps to roll back
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MigrationPlan {
+    pub id: String,
+    pub name: String,
+    pub waves: Vec<MigrationWave>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MigrationWave {
+    pub name: String,
+    pub steps: Vec<PlannedStep>,
+    // Pause for operator approval before the wave starts
+    #[serde(default)]
+    pub requires_approval: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlannedStep {
+    pub id: String,
+    pub kind: String,
+    #[serde(default)]
+    pub params: HashMap<String, Value>,
+}
+
+impl PlannedStep {
+    pub fn param_str(&self, name: &str) -> AutomationResult<&str> {
+        self.params
+            .get(name)
+            .and_then(Value::as_str)
+            .ok_or_else(|| AutomationError::Validation(format!("Step {} needs a `{}` parameter", self.id, name)))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepStatus {
+    Pending,
+    Running,
+    Succeeded,
+    Failed,
+    RollingBack,
+    RolledBack,
+    RollbackFailed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StepLog {
+    pub at: DateTime<Utc>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StepState {
+    pub step_id: String,
+    pub wave: usize,
+    pub status: StepStatus,
+    pub attempts: u32,
+    pub output: Option<Value>,
+    pub error: Option<String>,
+    pub logs: Vec<StepLog>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+impl StepState {
+    fn log(&mut self, message: impl Into<String>) {
+        self.logs.push(StepLog { at: Utc::now(), message: message.into() });
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum ExecutionStatus {
+    Running,
+    AwaitingApproval { wave: usize },
+    // Waiting for the operator to retry the step or roll the wave back
+    Failed { wave: usize, step_id: String },
+    RollingBack { wave: usize },
+    RolledBack { wave: usize },
+    RollbackFailed { wave: usize },
+    Completed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WaveApproval {
+    pub wave: usize,
+    pub approved_by: String,
+    pub approved_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MigrationExecution {
+    pub id: String,
+    pub plan: MigrationPlan,
+    pub status: ExecutionStatus,
+    // The wave being run, or the next one once a wave completes
+    pub wave: usize,
+    pub steps: Vec<StepState>,
+    pub approvals: Vec<WaveApproval>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl MigrationExecution {
+    fn new(plan: MigrationPlan, now: DateTime<Utc>) -> Self {
+        let steps = plan
+            .waves
+            .iter()
+            .enumerate()
+            .flat_map(|(wave, w)| {
+                w.steps.iter().map(move |step| StepState {
+                    step_id: step.id.clone(),
+                    wave,
+                    status: StepStatus::Pending,
+                    attempts: 0,
+                    output: None,
+                    error: None,
+                    logs: Vec::new(),
+                    started_at: None,
+                    finished_at: None,
+                })
+            })
+            .collect();
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            plan,
+            status: ExecutionStatus::Running,
+            wave: 0,
+            steps,
+            approvals: Vec::new(),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    pub fn step(&self, step_id: &str) -> Option<&StepState> {
+        self.steps.iter().find(|s| s.step_id == step_id)
+    }
+
+    fn wave_indices(&self, wave: usize) -> Vec<usize> {
+        (0..self.steps.len()).filter(|&i| self.steps[i].wave == wave).collect()
+    }
+
+    fn planned(&self, index: usize) -> PlannedStep {
+        let state = &self.steps[index];
+        self.plan.waves[state.wave]
+            .steps
+            .iter()
+            .find(|s| s.id == state.step_id)
+            .cloned()
+            .expect("step state always matches a planned step")
+    }
+
+    fn needs_approval(&self, wave: usize) -> bool {
+        self.plan.waves.get(wave).is_some_and(|w| w.requires_approval)
+            && !self.approvals.iter().any(|a| a.wave == wave)
+    }
+
+    // After `wave` finished: pause for approval, or carry on with the next wave
+    fn advance(&mut self) {
+        self.wave += 1;
+        self.status = if self.wave >= self.plan.waves.len() {
+            ExecutionStatus::Completed
+        } else if self.needs_approval(self.wave) {
+            ExecutionStatus::AwaitingApproval { wave: self.wave }
+        } else {
+            ExecutionStatus::Running
+        };
+    }
+}
+
+// What a step sees while running: outputs of the steps completed before it, and a log
+pub struct StepContext {
+    pub execution_id: String,
+    outputs: HashMap<String, Value>,
+    logs: StdMutex<Vec<StepLog>>,
+}
+
+impl StepContext {
+    fn for_execution(execution: &MigrationExecution) -> Self {
+        let outputs = execution
+            .steps
+            .iter()
+            .filter_map(|s| s.output.clone().map(|output| (s.step_id.clone(), output)))
+            .collect();
+        Self { execution_id: execution.id.clone(), outputs, logs: StdMutex::new(Vec::new()) }
+    }
+
+    pub fn output(&self, step_id: &str) -> Option<&Value> {
+        self.outputs.get(step_id)
+    }
+
+    // The output of the earlier step named by the `param` parameter
+    pub fn output_of(&self, step: &PlannedStep, param: &str) -> AutomationResult<&Value> {
+        let from = step.param_str(param)?;
+        self.output(from).ok_or_else(|| {
+            AutomationError::Validation(format!("Step {} depends on {}, which has not completed", step.id, from))
+        })
+    }
+
+    pub fn log(&self, message: impl Into<String>) {
+        self.logs.lock().unwrap().push(StepLog { at: Utc::now(), message: message.into() });
+    }
+
+    fn take_logs(&self) -> Vec<StepLog> {
+        std::mem::take(&mut *self.logs.lock().unwrap())
+    }
+}
+
+#[async_trait]
+pub trait MigrationStep: Send + Sync {
+    fn kind(&self) -> &str;
+    // Must be safe to run again: after a crash the step that was in flight is re-run.
+    // The returned output is persisted and handed to later steps and rollback hooks.
+    async fn execute(&self, step: &PlannedStep, ctx: &StepContext) -> AutomationResult<Value>;
+}
+
+// Undoes a completed step, given the output it returned
+#[async_trait]
+pub trait RollbackHook: Send + Sync {
+    async fn rollback(&self, step: &PlannedStep, output: &Value, ctx: &StepContext) -> AutomationResult<()>;
+}
+
+#[async_trait]
+pub trait ExecutionStore: Send + Sync {
+    async fn save(&self, execution: &MigrationExecution) -> AutomationResult<()>;
+    async fn load(&self, id: &str) -> AutomationResult<Option<MigrationExecution>>;
+    async fn list(&self) -> AutomationResult<Vec<MigrationExecution>>;
+}
+
+// Keeps each execution as a JSON object; every state change is written before the executor
+// moves on, so a restarted process picks up exactly where the last one stopped
+pub struct ObjectExecutionStore {
+    objects: Arc<dyn ObjectStore>,
+}
+
+impl ObjectExecutionStore {
+    pub fn new(objects: Arc<dyn ObjectStore>) -> Self {
+        Self { objects }
+    }
+
+    fn key(id: &str) -> String {
+        format!("{}{}.json", EXECUTION_PREFIX, id)
+    }
+}
+
+#[async_trait]
+impl ExecutionStore for ObjectExecutionStore {
+    async fn save(&self, execution: &MigrationExecution) -> AutomationResult<()> {
+        let data = serde_json::to_vec(execution).map_err(|e| AutomationError::Internal(e.to_string()))?;
+        self.objects.put(&Self::key(&execution.id), data).await
+    }
+
+    async fn load(&self, id: &str) -> AutomationResult<Option<MigrationExecution>> {
+        let key = Self::key(id);
+        if !self.objects.exists(&key).await? {
+            return Ok(None);
+        }
+        let data = self.objects.get(&key).await?;
+        serde_json::from_slice(&data)
+            .map(Some)
+            .map_err(|e| AutomationError::Internal(format!("Corrupt migration execution {}: {}", id, e)))
+    }
+
+    async fn list(&self) -> AutomationResult<Vec<MigrationExecution>> {
+        let mut executions = Vec::new();
+        for key in self.objects.list(EXECUTION_PREFIX).await? {
+            let Some(id) = key.strip_prefix(EXECUTION_PREFIX).and_then(|k| k.strip_suffix(".json")) else {
+                continue;
+            };
+            executions.extend(self.load(id).await?);
+        }
+        Ok(executions)
+    }
+}
+
+// Executions currently being driven by this process
+struct Claim<'a> {
+    active: &'a StdMutex<HashSet<String>>,
+    id: String,
+}
+
+impl Drop for Claim<'_> {
+    fn drop(&mut self) {
+        self.active.lock().unwrap().remove(&self.id);
+    }
+}
+
+pub struct MigrationExecutor {
+    store: Arc<dyn ExecutionStore>,
+    steps: HashMap<String, Arc<dyn MigrationStep>>,
+    rollback_hooks: HashMap<String, Arc<dyn RollbackHook>>,
+    active: StdMutex<HashSet<String>>,
+}
+
+impl MigrationExecutor {
+    pub fn new(store: Arc<dyn ExecutionStore>) -> Self {
+        Self {
+            store,
+            steps: HashMap::new(),
+            rollback_hooks: HashMap::new(),
+            active: StdMutex::new(HashSet::new()),
+        }
+    }
+
+    pub fn with_step(mut self, step: Arc<dyn MigrationStep>) -> Self {
+        self.steps.insert(step.kind().to_string(), step);
+        self
+    }
+
+    // Steps of a kind without a hook are left in place when their wave is rolled back
+    pub fn with_rollback_hook(mut self, kind: impl Into<String>, hook: Arc<dyn RollbackHook>) -> Self {
+        self.rollback_hooks.insert(kind.into(), hook);
+        self
+    }
+
+    fn claim(&self, id: &str) -> AutomationResult<Claim<'_>> {
+        if !self.active.lock().unwrap().insert(id.to_string()) {
+            return Err(AutomationError::Conflict(format!("Migration {} is already running", id)));
+        }
+        Ok(Claim { active: &self.active, id: id.to_string() })
+    }
+
+    async fn save(&self, execution: &mut MigrationExecution) -> AutomationResult<()> {
+        execution.updated_at = Utc::now();
+        self.store.save(execution).await
+    }
+
+    pub async fn get(&self, id: &str) -> AutomationResult<MigrationExecution> {
+        self.store
+            .load(id)
+            .await?
+            .ok_or_else(|| AutomationError::NotFound(format!("Migration execution {} not found", id)))
+    }
+
+    // Persists a new execution and runs it until it completes, fails or needs approval
+    pub async fn start(&self, plan: MigrationPlan) -> AutomationResult<MigrationExecution> {
+        let mut ids = HashSet::new();
+        for step in plan.waves.iter().flat_map(|w| &w.steps) {
+            if !ids.insert(step.id.as_str()) {
+                return Err(AutomationError::Validation(format!("Step id {} is used more than once", step.id)));
+            }
+            if !self.steps.contains_key(&step.kind) {
+                return Err(AutomationError::Validation(format!(
+                    "Step {} has kind {}, which has no registered implementation",
+                    step.id, step.kind
+                )));
+            }
+        }
+        let mut execution = MigrationExecution::new(plan, Utc::now());
+        if execution.needs_approval(0) {
+            execution.status = ExecutionStatus::AwaitingApproval { wave: 0 };
+        }
+        let _claim = self.claim(&execution.id)?;
+        self.save(&mut execution).await?;
+        info!("Started migration {} for plan {}", execution.id, execution.plan.id);
+        self.drive(execution).await
+    }
+
+    pub async fn approve(&self, id: &str, approved_by: &str) -> AutomationResult<MigrationExecution> {
+        let _claim = self.claim(id)?;
+        let mut execution = self.get(id).await?;
+        let ExecutionStatus::AwaitingApproval { wave } = execution.status else {
+            return Err(AutomationError::Conflict(format!("Migration {} is not waiting for approval", id)));
+        };
+        execution.approvals.push(WaveApproval { wave, approved_by: approved_by.to_string(), approved_at: Utc::now() });
+        execution.status = ExecutionStatus::Running;
+        self.save(&mut execution).await?;
+        info!("Wave {} of migration {} approved by {}", wave, id, approved_by);
+        self.drive(execution).await
+    }
+
+    // Re-runs the failed step and carries on with the wave
+    pub async fn retry(&self, id: &str) -> AutomationResult<MigrationExecution> {
+        let _claim = self.claim(id)?;
+        let mut execution = self.get(id).await?;
+        let ExecutionStatus::Failed { step_id, .. } = &execution.status else {
+            return Err(AutomationError::Conflict(format!("Migration {} has not failed", id)));
+        };
+        let step_id = step_id.clone();
+        if let Some(step) = execution.steps.iter_mut().find(|s| s.step_id == step_id) {
+            step.status = StepStatus::Pending;
+            step.error = None;
+        }
+        execution.status = ExecutionStatus::Running;
+        self.save(&mut execution).await?;
+        self.drive(execution).await
+    }
+
+    // Undoes the completed steps of the failed wave, newest first
+    pub async fn rollback(&self, id: &str) -> AutomationResult<MigrationExecution> {
+        let _claim = self.claim(id)?;
+        let mut execution = self.get(id).await?;
+        let ExecutionStatus::Failed { wave, .. } = execution.status else {
+            return Err(AutomationError::Conflict(format!("Migration {} has not failed", id)));
+        };
+        execution.status = ExecutionStatus::RollingBack { wave };
+        self.save(&mut execution).await?;
+        self.drive(execution).await
+    }
+
+    // Called on startup: continues every execution that was running or rolling back when the
+    // previous process stopped
+    pub async fn resume_incomplete(&self) -> AutomationResult<Vec<MigrationExecution>> {
+        let mut resumed = Vec::new();
+        for execution in self.store.list().await? {
+            if !matches!(execution.status, ExecutionStatus::Running | ExecutionStatus::RollingBack { .. }) {
+                continue;
+            }
+            let Ok(_claim) = self.claim(&execution.id) else {
+                continue;
+            };
+            info!("Resuming migration {} at wave {}", execution.id, execution.wave);
+            resumed.push(self.drive(execution).await?);
+        }
+        Ok(resumed)
+    }
+
+    async fn drive(&self, mut execution: MigrationExecution) -> AutomationResult<MigrationExecution> {
+        loop {
+            match execution.status {
+                ExecutionStatus::Running => {}
+                ExecutionStatus::RollingBack { wave } => {
+                    self.roll_back_wave(&mut execution, wave).await?;
+                    return Ok(execution);
+                }
+                _ => return Ok(execution),
+            }
+            if !self.run_wave(&mut execution).await? {
+                return Ok(execution);
+            }
+            execution.advance();
+            self.save(&mut execution).await?;
+            if execution.status == ExecutionStatus::Completed {
+                info!("Migration {} completed", execution.id);
+            }
+        }
+    }
+
+    // Returns false if a step failed
+    async fn run_wave(&self, execution: &mut MigrationExecution) -> AutomationResult<bool> {
+        let wave = execution.wave;
+        for index in execution.wave_indices(wave) {
+            if execution.steps[index].status == StepStatus::Succeeded {
+                continue;
+            }
+            let planned = execution.planned(index);
+            let implementation = self.steps.get(&planned.kind).cloned().ok_or_else(|| {
+                AutomationError::Config(format!("No implementation registered for step kind {}", planned.kind))
+            })?;
+
+            // Recorded before running so a crash mid-step shows up as an interrupted attempt
+            let state = &mut execution.steps[index];
+            if state.status == StepStatus::Running {
+                state.log("Resuming after an interrupted attempt");
+            }
+            state.status = StepStatus::Running;
+            state.attempts += 1;
+            state.started_at = Some(Utc::now());
+            self.save(execution).await?;
+
+            let ctx = StepContext::for_execution(execution);
+            let result = implementation.execute(&planned, &ctx).await;
+            let state = &mut execution.steps[index];
+            state.logs.extend(ctx.take_logs());
+            state.finished_at = Some(Utc::now());
+            match result {
+                Ok(output) => {
+                    state.status = StepStatus::Succeeded;
+                    state.output = Some(output);
+                    self.save(execution).await?;
+                }
+                Err(e) => {
+                    warn!("Migration {} step {} failed: {}", execution.id, planned.id, e);
+                    state.status = StepStatus::Failed;
+                    state.error = Some(e.to_string());
+                    execution.status = ExecutionStatus::Failed { wave, step_id: planned.id };
+                    self.save(execution).await?;
+                    return Ok(false);
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    async fn roll_back_wave(&self, execution: &mut MigrationExecution, wave: usize) -> AutomationResult<()> {
+        let mut failed = false;
+        for index in execution.wave_indices(wave).into_iter().rev() {
+            if !matches!(execution.steps[index].status, StepStatus::Succeeded | StepStatus::RollingBack) {
+                continue;
+            }
+            let planned = execution.planned(index);
+            let Some(hook) = self.rollback_hooks.get(&planned.kind).cloned() else {
+                execution.steps[index].log("No rollback hook registered; left in place");
+                continue;
+            };
+            let output = execution.steps[index].output.clone().unwrap_or(Value::Null);
+            execution.steps[index].status = StepStatus::RollingBack;
+            self.save(execution).await?;
+
+            let ctx = StepContext::for_execution(execution);
+            let result = hook.rollback(&planned, &output, &ctx).await;
+            let state = &mut execution.steps[index];
+            state.logs.extend(ctx.take_logs());
+            match result {
+                Ok(()) => {
+                    state.status = StepStatus::RolledBack;
+                    state.log("Rolled back");
+                }
+                Err(e) => {
+                    warn!("Rollback of migration {} step {} failed: {}", execution.id, planned.id, e);
+                    state.status = StepStatus::RollbackFailed;
+                    state.error = Some(e.to_string());
+                    failed = true;
+                }
+            }
+            self.save(execution).await?;
+        }
+        execution.status = if failed {
+            ExecutionStatus::RollbackFailed { wave }
+        } else {
+            ExecutionStatus::RolledBack { wave }
+        };
+        self.save(execution).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::artifact::LocalObjectStore;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    // Records every call in a shared journal; can be told to fail or to "crash" mid-step
+    struct Recorded {
+        kind: &'static str,
+        journal: Arc<StdMutex<Vec<String>>>,
+        fail: Arc<AtomicBool>,
+        crash: Arc<AtomicBool>,
+    }
+
+    #[async_trait]
+    impl MigrationStep for Recorded {
+        fn kind(&self) -> &str {
+            self.kind
+        }
+
+        async fn execute(&self, step: &PlannedStep, ctx: &StepContext) -> AutomationResult<Value> {
+            self.journal.lock().unwrap().push(format!("run {}", step.id));
+            if self.crash.swap(false, Ordering::SeqCst) {
+                // Never completes, as if the process died here
+                std::future::pending::<()>().await;
+            }
+            if step.params.contains_key("fail") && self.fail.load(Ordering::SeqCst) {
+                return Err(AutomationError::Task(format!("{} exploded", step.id)));
+            }
+            ctx.log(format!("ran {}", step.id));
+            Ok(serde_json::json!({ "id": step.id }))
+        }
+    }
+
+    #[async_trait]
+    impl RollbackHook for Recorded {
+        async fn rollback(&self, step: &PlannedStep, output: &Value, _ctx: &StepContext) -> AutomationResult<()> {
+            assert_eq!(output["id"], step.id.as_str());
+            self.journal.lock().unwrap().push(format!("undo {}", step.id));
+            Ok(())
+        }
+    }
+
+    struct Harness {
+        journal: Arc<StdMutex<Vec<String>>>,
+        fail: Arc<AtomicBool>,
+        crash: Arc<AtomicBool>,
+    }
+
+    impl Harness {
+        fn new() -> Self {
+            Self {
+                journal: Arc::default(),
+                fail: Arc::new(AtomicBool::new(true)),
+                crash: Arc::new(AtomicBool::new(false)),
+            }
+        }
+
+        fn executor(&self, store: Arc<dyn ExecutionStore>) -> MigrationExecutor {
+            let step = |kind| {
+                Arc::new(Recorded { kind, journal: self.journal.clone(), fail: self.fail.clone(), crash: self.crash.clone() })
+            };
+            let (create, sync) = (step("create_target"), step("sync_data"));
+            MigrationExecutor::new(store)
+                .with_step(create.clone())
+                .with_step(sync)
+                .with_rollback_hook("create_target", create)
+        }
+
+        fn journal(&self) -> Vec<String> {
+            self.journal.lock().unwrap().clone()
+        }
+    }
+
+    fn step(id: &str, kind: &str) -> PlannedStep {
+        PlannedStep { id: id.into(), kind: kind.into(), params: HashMap::new() }
+    }
+
+    fn plan() -> MigrationPlan {
+        let mut failing = step("sync-db", "sync_data");
+        failing.params.insert("fail".into(), true.into());
+        MigrationPlan {
+            id: "plan-1".into(),
+            name: "orders to aws".into(),
+            waves: vec![
+                MigrationWave { name: "pilot".into(), steps: vec![step("create-cache", "create_target")], requires_approval: false },
+                MigrationWave {
+                    name: "data".into(),
+                    steps: vec![step("create-db", "create_target"), step("create-queue", "create_target"), failing],
+                    requires_approval: true,
+                },
+            ],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mid_wave_failure_rolls_back_completed_steps() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Arc::new(ObjectExecutionStore::new(Arc::new(LocalObjectStore::new(dir.path()))));
+        let harness = Harness::new();
+        let executor = harness.executor(store.clone());
+
+        let execution = executor.start(plan()).await.unwrap();
+        assert_eq!(execution.status, ExecutionStatus::AwaitingApproval { wave: 1 });
+        assert_eq!(harness.journal(), vec!["run create-cache"]);
+
+        let execution = executor.approve(&execution.id, "ops@sirsi").await.unwrap();
+        assert_eq!(execution.status, ExecutionStatus::Failed { wave: 1, step_id: "sync-db".into() });
+        assert_eq!(execution.step("sync-db").unwrap().error.as_deref(), Some("Task error: sync-db exploded"));
+        assert_eq!(execution.step("create-db").unwrap().logs[0].message, "ran create-db");
+
+        let execution = executor.rollback(&execution.id).await.unwrap();
+        assert_eq!(execution.status, ExecutionStatus::RolledBack { wave: 1 });
+        // Only the failed wave is undone, newest step first
+        assert_eq!(
+            harness.journal()[3..],
+            ["run sync-db", "undo create-queue", "undo create-db"].map(String::from)
+        );
+        assert_eq!(execution.step("create-db").unwrap().status, StepStatus::RolledBack);
+        assert_eq!(execution.step("create-cache").unwrap().status, StepStatus::Succeeded);
+        assert_eq!(execution.step("sync-db").unwrap().status, StepStatus::Failed);
+        assert!(matches!(executor.retry(&execution.id).await, Err(AutomationError::Conflict(_))));
+        assert_eq!(store.load(&execution.id).await.unwrap().unwrap().status, ExecutionStatus::RolledBack { wave: 1 });
+    }
+
+    #[tokio::test]
+    async fn test_resume_after_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = || Arc::new(ObjectExecutionStore::new(Arc::new(LocalObjectStore::new(dir.path()))));
+        let harness = Harness::new();
+        harness.fail.store(false, Ordering::SeqCst);
+        let mut plan = plan();
+        plan.waves[1].requires_approval = false;
+
+        // The first process dies halfway through the first step
+        harness.crash.store(true, Ordering::SeqCst);
+        let crashed = harness.executor(store());
+        let attempt = tokio::time::timeout(std::time::Duration::from_millis(200), crashed.start(plan)).await;
+        assert!(attempt.is_err());
+        drop(crashed);
+
+        let restarted = harness.executor(store());
+        let resumed = restarted.resume_incomplete().await.unwrap();
+        assert_eq!(resumed.len(), 1);
+        let execution = &resumed[0];
+        assert_eq!(execution.status, ExecutionStatus::Completed);
+        let cache = execution.step("create-cache").unwrap();
+        assert_eq!((cache.status, cache.attempts), (StepStatus::Succeeded, 2));
+        assert_eq!(cache.logs[0].message, "Resuming after an interrupted attempt");
+        assert_eq!(
+            harness.journal(),
+            ["run create-cache", "run create-cache", "run create-db", "run create-queue", "run sync-db"].map(String::from)
+        );
+        assert!(restarted.resume_incomplete().await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,383 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sirsi_data_services::database::{DatabaseInstance, DatabaseManager};
+use sirsi_data_services::DataError;
+use sirsi_network_services::dns::RecordSetManager;
+
+use super::{MigrationStep, PlannedStep, RollbackHook, StepContext};
+use crate::error::{AutomationError, AutomationResult};
+
+pub const CREATE_TARGET: &str = "create_target";
+pub const SYNC_DATA: &str = "sync_data";
+pub const CUTOVER_DNS: &str = "cutover_dns";
+pub const DECOMMISSION_SOURCE: &str = "decommission_source";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProvisionedTarget {
+    pub resource_id: String,
+    pub endpoint: String,
+}
+
+// Compute capacity for migration targets. `key` is stable across retries of the same step,
+// so provisioning again with a key that was already used must return the existing resource.
+#[async_trait]
+pub trait ComputeProvisioner: Send + Sync {
+    async fn provision(&self, key: &str, spec: &Value) -> AutomationResult<ProvisionedTarget>;
+    async fn terminate(&self, resource_id: &str) -> AutomationResult<()>;
+}
+
+// Which provider a create or decommission step talks to, from its `target` parameter
+enum Target<'a> {
+    Database(&'a Arc<dyn DatabaseManager>),
+    Compute(&'a Arc<dyn ComputeProvisioner>),
+}
+
+fn target<'a>(
+    step: &PlannedStep,
+    databases: &'a Option<Arc<dyn DatabaseManager>>,
+    compute: &'a Option<Arc<dyn ComputeProvisioner>>,
+) -> AutomationResult<Target<'a>> {
+    let kind = step.param_str("target")?;
+    let missing = || AutomationError::Config(format!("Step {} targets {}, which is not configured", step.id, kind));
+    match kind {
+        "database" => databases.as_ref().map(Target::Database).ok_or_else(missing),
+        "compute" => compute.as_ref().map(Target::Compute).ok_or_else(missing),
+        other => Err(AutomationError::Validation(format!("Step {} has unknown target {}", step.id, other))),
+    }
+}
+
+fn output_str<'a>(output: &'a Value, field: &str) -> AutomationResult<&'a str> {
+    output
+        .get(field)
+        .and_then(Value::as_str)
+        .ok_or_else(|| AutomationError::Internal(format!("Step output has no `{}`", field)))
+}
+
+// Creates the target resource described by the `spec` parameter
+#[derive(Default)]
+pub struct CreateTargetResource {
+    databases: Option<Arc<dyn DatabaseManager>>,
+    compute: Option<Arc<dyn ComputeProvisioner>>,
+}
+
+impl CreateTargetResource {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_databases(mut self, databases: Arc<dyn DatabaseManager>) -> Self {
+        self.databases = Some(databases);
+        self
+    }
+
+    pub fn with_compute(mut self, compute: Arc<dyn ComputeProvisioner>) -> Self {
+        self.compute = Some(compute);
+        self
+    }
+}
+
+#[async_trait]
+impl MigrationStep for CreateTargetResource {
+    fn kind(&self) -> &str {
+        CREATE_TARGET
+    }
+
+    async fn execute(&self, step: &PlannedStep, ctx: &StepContext) -> AutomationResult<Value> {
+        let spec = step
+            .params
+            .get("spec")
+            .ok_or_else(|| AutomationError::Validation(format!("Step {} needs a `spec` parameter", step.id)))?;
+        let target = match target(step, &self.databases, &self.compute)? {
+            Target::Database(databases) => {
+                let instance: DatabaseInstance = serde_json::from_value(spec.clone())
+                    .map_err(|e| AutomationError::Validation(format!("Invalid database spec for {}: {}", step.id, e)))?;
+                // Created by an attempt that was interrupted before it could record its output
+                let instance = match databases.get_instance(&instance.id).await {
+                    Ok(existing) => {
+                        ctx.log(format!("Database {} already exists", existing.id));
+                        existing
+                    }
+                    Err(DataError::NotFound(_)) => databases.create_instance(instance).await?,
+                    Err(e) => return Err(e.into()),
+                };
+                ProvisionedTarget { endpoint: format!("{}:{}", instance.endpoint, instance.port), resource_id: instance.id }
+            }
+            Target::Compute(compute) => compute.provision(&format!("{}/{}", ctx.execution_id, step.id), spec).await?,
+        };
+        ctx.log(format!("Created {} at {}", target.resource_id, target.endpoint));
+        Ok(json!({ "resource_id": target.resource_id, "endpoint": target.endpoint }))
+    }
+}
+
+#[async_trait]
+impl RollbackHook for CreateTargetResource {
+    async fn rollback(&self, step: &PlannedStep, output: &Value, ctx: &StepContext) -> AutomationResult<()> {
+        let resource_id = output_str(output, "resource_id")?;
+        match target(step, &self.databases, &self.compute)? {
+            Target::Database(databases) => match databases.delete_instance(resource_id).await {
+                Ok(()) | Err(DataError::NotFound(_)) => {}
+                Err(e) => return Err(e.into()),
+            },
+            Target::Compute(compute) => compute.terminate(resource_id).await?,
+        }
+        ctx.log(format!("Deleted {}", resource_id));
+        Ok(())
+    }
+}
+
+// Copies a source database into the target created by the step named in `target_step`, by
+// restoring a fresh backup of the source over it
+pub struct SyncData {
+    databases: Arc<dyn DatabaseManager>,
+}
+
+impl SyncData {
+    pub fn new(databases: Arc<dyn DatabaseManager>) -> Self {
+        Self { databases }
+    }
+}
+
+#[async_trait]
+impl MigrationStep for SyncData {
+    fn kind(&self) -> &str {
+        SYNC_DATA
+    }
+
+    async fn execute(&self, step: &PlannedStep, ctx: &StepContext) -> AutomationResult<Value> {
+        let source_id = step.param_str("source_id")?;
+        let target_id = output_str(ctx.output_of(step, "target_step")?, "resource_id")?;
+        let backup = self.databases.create_backup(source_id).await?;
+        ctx.log(format!("Backed up {} as {}", source_id, backup.id));
+        self.databases.restore_backup(&backup.id, target_id).await?;
+        ctx.log(format!("Restored {} into {}", backup.id, target_id));
+        Ok(json!({ "backup_id": backup.id, "source_id": source_id, "target_id": target_id }))
+    }
+}
+
+// Points an existing record set at the new target: the `records` parameter, or the endpoint
+// of the step named in `target_step`
+pub struct CutoverDns {
+    records: Arc<dyn RecordSetManager>,
+}
+
+impl CutoverDns {
+    pub fn new(records: Arc<dyn RecordSetManager>) -> Self {
+        Self { records }
+    }
+}
+
+#[async_trait]
+impl MigrationStep for CutoverDns {
+    fn kind(&self) -> &str {
+        CUTOVER_DNS
+    }
+
+    async fn execute(&self, step: &PlannedStep, ctx: &StepContext) -> AutomationResult<Value> {
+        let zone_id = step.param_str("zone_id")?;
+        let record_id = step.param_str("record_id")?;
+        let desired: Vec<String> = match step.params.get("records") {
+            Some(records) => serde_json::from_value(records.clone())
+                .map_err(|e| AutomationError::Validation(format!("Invalid records for {}: {}", step.id, e)))?,
+            None => {
+                let endpoint = output_str(ctx.output_of(step, "target_step")?, "endpoint")?;
+                // Record values are host names or addresses, not host:port
+                vec![endpoint.split(':').next().unwrap_or(endpoint).to_string()]
+            }
+        };
+
+        let mut record = self.records.get_record_set(zone_id, record_id).await?;
+        let previous = std::mem::replace(&mut record.records, desired.clone());
+        if previous == desired {
+            ctx.log(format!("{} already points at {:?}", record.name, desired));
+        } else {
+            self.records.modify_record_set(record.clone()).await?;
+            ctx.log(format!("Moved {} from {:?} to {:?}", record.name, previous, desired));
+        }
+        Ok(json!({ "zone_id": zone_id, "record_id": record_id, "previous": previous, "records": desired }))
+    }
+}
+
+#[async_trait]
+impl RollbackHook for CutoverDns {
+    async fn rollback(&self, _step: &PlannedStep, output: &Value, ctx: &StepContext) -> AutomationResult<()> {
+        let zone_id = output_str(output, "zone_id")?;
+        let record_id = output_str(output, "record_id")?;
+        let previous: Vec<String> = serde_json::from_value(output["previous"].clone())
+            .map_err(|e| AutomationError::Internal(format!("Step output has no previous records: {}", e)))?;
+        let mut record = self.records.get_record_set(zone_id, record_id).await?;
+        record.records = previous;
+        ctx.log(format!("Restored {} to {:?}", record.name, record.records));
+        self.records.modify_record_set(record).await?;
+        Ok(())
+    }
+}
+
+// Deletes the source resource named by `source_id`. Deliberately has no rollback hook.
+#[derive(Default)]
+pub struct DecommissionSource {
+    databases: Option<Arc<dyn DatabaseManager>>,
+    compute: Option<Arc<dyn ComputeProvisioner>>,
+}
+
+impl DecommissionSource {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_databases(mut self, databases: Arc<dyn DatabaseManager>) -> Self {
+        self.databases = Some(databases);
+        self
+    }
+
+    pub fn with_compute(mut self, compute: Arc<dyn ComputeProvisioner>) -> Self {
+        self.compute = Some(compute);
+        self
+    }
+}
+
+#[async_trait]
+impl MigrationStep for DecommissionSource {
+    fn kind(&self) -> &str {
+        DECOMMISSION_SOURCE
+    }
+
+    async fn execute(&self, step: &PlannedStep, ctx: &StepContext) -> AutomationResult<Value> {
+        let source_id = step.param_str("source_id")?;
+        match target(step, &self.databases, &self.compute)? {
+            Target::Database(databases) => match databases.delete_instance(source_id).await {
+                Ok(()) => {}
+                Err(DataError::NotFound(_)) => ctx.log(format!("{} was already gone", source_id)),
+                Err(e) => return Err(e.into()),
+            },
+            Target::Compute(compute) => compute.terminate(source_id).await?,
+        }
+        ctx.log(format!("Decommissioned {}", source_id));
+        Ok(json!({ "source_id": source_id }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::artifact::LocalObjectStore;
+    use crate::migration::{ExecutionStatus, MigrationExecutor, MigrationPlan, MigrationWave, ObjectExecutionStore, StepStatus};
+    use sirsi_network_services::dns::resolver::DnsResolver;
+    use sirsi_network_services::dns::{
+        DnssecStatus, RecordSet, RecordType, RoutingPolicy, SOARecord, Zone, ZoneManager, ZoneStatus, ZoneType,
+    };
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct FakeCompute {
+        live: Mutex<HashMap<String, String>>,
+    }
+
+    #[async_trait]
+    impl ComputeProvisioner for FakeCompute {
+        async fn provision(&self, key: &str, _spec: &Value) -> AutomationResult<ProvisionedTarget> {
+            let mut live = self.live.lock().unwrap();
+            let id = format!("i-{}", live.len() + 1);
+            let id = live.entry(key.to_string()).or_insert(id).clone();
+            Ok(ProvisionedTarget { endpoint: format!("10.0.0.{}:443", live.len()), resource_id: id })
+        }
+
+        async fn terminate(&self, resource_id: &str) -> AutomationResult<()> {
+            self.live.lock().unwrap().retain(|_, id| id != resource_id);
+            Ok(())
+        }
+    }
+
+    fn step(id: &str, kind: &str, params: Value) -> PlannedStep {
+        PlannedStep { id: id.into(), kind: kind.into(), params: serde_json::from_value(params).unwrap() }
+    }
+
+    async fn dns() -> Arc<DnsResolver> {
+        let resolver = Arc::new(DnsResolver::new());
+        resolver
+            .create_zone(Zone {
+                id: "zone-1".into(),
+                name: "example.com".into(),
+                domain: "example.com".into(),
+                zone_type: ZoneType::Public,
+                status: ZoneStatus::Active,
+                nameservers: vec!["ns1.example.com".into()],
+                soa_record: SOARecord {
+                    mname: "ns1.example.com".into(),
+                    rname: "hostmaster.example.com".into(),
+                    serial: 1,
+                    refresh: 7200,
+                    retry: 900,
+                    expire: 1_209_600,
+                    minimum: 300,
+                },
+                dnssec_status: DnssecStatus::Disabled,
+                tags: HashMap::new(),
+                created_at: chrono::Utc::now(),
+                updated_at: chrono::Utc::now(),
+            })
+            .await
+            .unwrap();
+        resolver
+            .create_record_set(RecordSet {
+                id: "app".into(),
+                zone_id: "zone-1".into(),
+                name: "app.example.com".into(),
+                record_type: RecordType::A,
+                ttl: 60,
+                records: vec!["192.0.2.10".into()],
+                routing_policy: Some(RoutingPolicy::Simple),
+                health_check: None,
+                alias_target: None,
+            })
+            .await
+            .unwrap();
+        resolver
+    }
+
+    #[tokio::test]
+    async fn test_cutover_wave_rolls_back_dns_and_target() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Arc::new(ObjectExecutionStore::new(Arc::new(LocalObjectStore::new(dir.path()))));
+        let compute = Arc::new(FakeCompute::default());
+        let resolver = dns().await;
+        let create = Arc::new(CreateTargetResource::new().with_compute(compute.clone()));
+        let cutover = Arc::new(CutoverDns::new(resolver.clone()));
+        let executor = MigrationExecutor::new(store)
+            .with_step(create.clone())
+            .with_step(cutover.clone())
+            .with_step(Arc::new(DecommissionSource::new()))
+            .with_rollback_hook(CREATE_TARGET, create)
+            .with_rollback_hook(CUTOVER_DNS, cutover);
+
+        let plan = MigrationPlan {
+            id: "plan-app".into(),
+            name: "app to aws".into(),
+            waves: vec![MigrationWave {
+                name: "cutover".into(),
+                steps: vec![
+                    step("create-app", CREATE_TARGET, json!({ "target": "compute", "spec": { "size": "m5.large" } })),
+                    step("dns-app", CUTOVER_DNS, json!({ "zone_id": "zone-1", "record_id": "app", "target_step": "create-app" })),
+                    // No database manager configured, so decommissioning fails
+                    step("retire-db", DECOMMISSION_SOURCE, json!({ "target": "database", "source_id": "db-old" })),
+                ],
+                requires_approval: false,
+            }],
+        };
+        let execution = executor.start(plan).await.unwrap();
+        assert!(matches!(execution.status, ExecutionStatus::Failed { ref step_id, .. } if step_id == "retire-db"));
+        let record = resolver.get_record_set("zone-1", "app").await.unwrap();
+        assert_eq!(record.records, vec!["10.0.0.1".to_string()]);
+        assert_eq!(compute.live.lock().unwrap().len(), 1);
+
+        let execution = executor.rollback(&execution.id).await.unwrap();
+        assert_eq!(execution.status, ExecutionStatus::RolledBack { wave: 0 });
+        assert_eq!(execution.step("dns-app").unwrap().status, StepStatus::RolledBack);
+        let record = resolver.get_record_set("zone-1", "app").await.unwrap();
+        assert_eq!(record.records, vec!["192.0.2.10".to_string()]);
+        assert!(compute.live.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,98 @@
+use std::time::Duration;
+
+use lettre::message::{header::ContentType, Mailbox};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AutomationError, AutomationResult};
+use super::RenderedMessage;
+
+const DEFAULT_SUBJECT: &str = "SirsiNexus notification";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmailConfig {
+    pub smtp_host: String,
+    pub smtp_port: u16,
+    pub starttls: bool,
+    pub username: Option<String>,
+    // Key vault secret holding the SMTP password
+    pub password_secret: Option<String>,
+    pub from: String,
+    pub to: Vec<String>,
+}
+
+impl EmailConfig {
+    pub fn new(smtp_host: impl Into<String>, from: impl Into<String>, to: Vec<String>) -> Self {
+        Self {
+            smtp_host: smtp_host.into(),
+            smtp_port: 587,
+            starttls: true,
+            username: None,
+            password_secret: None,
+            from: from.into(),
+            to,
+        }
+    }
+
+    pub fn with_port(mut self, port: u16) -> Self {
+        self.smtp_port = port;
+        self
+    }
+
+    pub fn with_starttls(mut self, starttls: bool) -> Self {
+        self.starttls = starttls;
+        self
+    }
+
+    pub fn with_credentials(mut self, username: impl Into<String>, password_secret: impl Into<String>) -> Self {
+        self.username = Some(username.into());
+        self.password_secret = Some(password_secret.into());
+        self
+    }
+}
+
+fn mailbox(address: &str) -> AutomationResult<Mailbox> {
+    address
+        .parse()
+        .map_err(|e| AutomationError::Validation(format!("Invalid email address '{}': {}", address, e)))
+}
+
+fn smtp_error(e: lettre::transport::smtp::Error) -> AutomationError {
+    if e.is_permanent() || e.is_client() {
+        AutomationError::Notification(format!("SMTP delivery rejected: {}", e))
+    } else {
+        AutomationError::Unavailable(format!("SMTP delivery failed: {}", e))
+    }
+}
+
+pub(crate) async fn send(
+    config: &EmailConfig,
+    password: Option<String>,
+    message: &RenderedMessage,
+) -> AutomationResult<()> {
+    let mut builder = Message::builder()
+        .from(mailbox(&config.from)?)
+        .subject(message.subject.as_deref().unwrap_or(DEFAULT_SUBJECT))
+        .header(ContentType::TEXT_PLAIN);
+    for to in &config.to {
+        builder = builder.to(mailbox(to)?);
+    }
+    let email = builder
+        .body(message.body.clone())
+        .map_err(|e| AutomationError::Validation(format!("Invalid email message: {}", e)))?;
+
+    let mut transport = if config.starttls {
+        AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.smtp_host).map_err(smtp_error)?
+    } else {
+        AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.smtp_host)
+    }
+    .port(config.smtp_port)
+    .timeout(Some(Duration::from_secs(30)));
+    if let (Some(username), Some(password)) = (&config.username, password) {
+        transport = transport.credentials(Credentials::new(username.clone(), password));
+    }
+
+    transport.build().send(email).await.map_err(smtp_error)?;
+    Ok(())
+}
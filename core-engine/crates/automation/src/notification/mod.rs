@@ -0,0 +1,679 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sirsi_common::{retry, RetryPolicy};
+use sirsi_key_vault::secret::{SecretManager, SecretValue};
+use sirsi_key_vault::KeyVaultError;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+use crate::error::{AutomationError, AutomationResult};
+use crate::workflow::{
+    ExecutionContext, FailureAction, ResourceUsage, RunStatus, Task, TaskError, TaskExecutor, TaskMetrics,
+    TaskResult, TaskType, Value, Workflow,
+};
+
+pub mod email;
+pub mod slack;
+pub mod template;
+
+pub use email::EmailConfig;
+pub use slack::SlackConfig;
+pub use template::{RenderContext, Template, TemplateScope};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ChannelKind {
+    Slack(SlackConfig),
+    Email(EmailConfig),
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct RateLimit {
+    pub max_messages: u32,
+    pub per_seconds: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationChannel {
+    pub id: String,
+    pub kind: ChannelKind,
+    pub rate_limit: Option<RateLimit>,
+}
+
+impl NotificationChannel {
+    pub fn slack(id: impl Into<String>, config: SlackConfig) -> Self {
+        Self {
+            id: id.into(),
+            kind: ChannelKind::Slack(config),
+            rate_limit: None,
+        }
+    }
+
+    pub fn email(id: impl Into<String>, config: EmailConfig) -> Self {
+        Self {
+            id: id.into(),
+            kind: ChannelKind::Email(config),
+            rate_limit: None,
+        }
+    }
+
+    pub fn with_rate_limit(mut self, max_messages: u32, per_seconds: u64) -> Self {
+        self.rate_limit = Some(RateLimit { max_messages, per_seconds });
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderedMessage {
+    pub subject: Option<String>,
+    pub body: String,
+}
+
+#[async_trait]
+pub trait SecretResolver: Send + Sync {
+    async fn resolve(&self, reference: &str) -> AutomationResult<String>;
+}
+
+// Resolves channel credentials from the key vault; only plain-text secrets are usable here
+pub struct KeyVaultSecrets {
+    manager: Arc<dyn SecretManager>,
+}
+
+impl KeyVaultSecrets {
+    pub fn new(manager: Arc<dyn SecretManager>) -> Self {
+        Self { manager }
+    }
+}
+
+#[async_trait]
+impl SecretResolver for KeyVaultSecrets {
+    async fn resolve(&self, reference: &str) -> AutomationResult<String> {
+        let secret = self.manager.get_secret(reference).await.map_err(|e| match e {
+            KeyVaultError::NotFound(_) => AutomationError::Config(format!("Secret {} not found", reference)),
+            KeyVaultError::Permission(msg) => AutomationError::Auth(msg),
+            other => AutomationError::Internal(format!("Failed to read secret {}: {}", reference, other)),
+        })?;
+        match secret.value {
+            SecretValue::Plain(value) => Ok(value),
+            _ => Err(AutomationError::Config(format!("Secret {} is not a plain-text value", reference))),
+        }
+    }
+}
+
+// Single delivery path shared by Notification tasks and on_failure notifications
+pub struct NotificationSender {
+    channels: HashMap<String, NotificationChannel>,
+    secrets: Arc<dyn SecretResolver>,
+    client: reqwest::Client,
+    retry_policy: RetryPolicy,
+    windows: Mutex<HashMap<String, VecDeque<Instant>>>,
+}
+
+impl NotificationSender {
+    pub fn new(secrets: Arc<dyn SecretResolver>) -> Self {
+        Self {
+            channels: HashMap::new(),
+            secrets,
+            client: reqwest::Client::new(),
+            retry_policy: RetryPolicy::new(3),
+            windows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn with_channel(mut self, channel: NotificationChannel) -> Self {
+        self.channels.insert(channel.id.clone(), channel);
+        self
+    }
+
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+
+    fn channel(&self, id: &str) -> AutomationResult<&NotificationChannel> {
+        self.channels
+            .get(id)
+            .ok_or_else(|| AutomationError::NotFound(format!("Notification channel {} not found", id)))
+    }
+
+    pub async fn send(&self, channel_id: &str, message: &RenderedMessage) -> AutomationResult<()> {
+        let channel = self.channel(channel_id)?;
+        self.check_rate_limit(channel).await?;
+        retry(&self.retry_policy, || self.deliver(channel, message)).await
+    }
+
+    // Sliding window per channel; over-limit messages are rejected rather than queued
+    async fn check_rate_limit(&self, channel: &NotificationChannel) -> AutomationResult<()> {
+        let Some(limit) = channel.rate_limit else {
+            return Ok(());
+        };
+        let window = Duration::from_secs(limit.per_seconds);
+        let now = Instant::now();
+
+        let mut windows = self.windows.lock().await;
+        let sent = windows.entry(channel.id.clone()).or_default();
+        while sent.front().is_some_and(|at| now.duration_since(*at) >= window) {
+            sent.pop_front();
+        }
+        if sent.len() >= limit.max_messages as usize {
+            return Err(AutomationError::Throttled(format!(
+                "Channel {} exceeded {} messages per {}s",
+                channel.id, limit.max_messages, limit.per_seconds
+            )));
+        }
+        sent.push_back(now);
+        Ok(())
+    }
+
+    async fn deliver(&self, channel: &NotificationChannel, message: &RenderedMessage) -> AutomationResult<()> {
+        match &channel.kind {
+            ChannelKind::Slack(config) => {
+                let webhook_url = self.secrets.resolve(&config.webhook_secret).await?;
+                slack::send(&self.client, &webhook_url, config, message).await
+            }
+            ChannelKind::Email(config) => {
+                let password = match &config.password_secret {
+                    Some(reference) => Some(self.secrets.resolve(reference).await?),
+                    None => None,
+                };
+                email::send(config, password, message).await
+            }
+        }
+    }
+
+    pub async fn notify_failure(
+        &self,
+        task: &Task,
+        context: &ExecutionContext,
+        error: &TaskError,
+    ) -> AutomationResult<bool> {
+        let Some(FailureAction::Notification { channel, message }) = &task.on_failure else {
+            return Ok(false);
+        };
+        let mut render_context = render_context(context);
+        render_context.error = Some(error.clone());
+        let rendered = RenderedMessage {
+            subject: Some(format!("Task {} failed", task.name)),
+            body: Template::parse(message)?.render(&render_context)?,
+        };
+        self.send(channel, &rendered).await?;
+        Ok(true)
+    }
+
+    // Surfaces unknown channels and unresolvable template references before a run starts
+    pub fn validate_workflow(&self, workflow: &Workflow) -> AutomationResult<()> {
+        let variables: HashSet<String> = workflow.variables.keys().cloned().collect();
+        for task in &workflow.tasks {
+            let scope = TemplateScope {
+                variables: variables.clone(),
+                upstream_tasks: upstream_tasks(workflow, &task.id),
+                has_error: false,
+            };
+            let context = |e: AutomationError| AutomationError::Validation(format!("Task {}: {}", task.id, e));
+
+            if let TaskType::Notification { channel } = &task.task_type {
+                self.channel(channel).map_err(context)?;
+                let (subject, body) = message_templates(task).map_err(context)?;
+                if let Some(subject) = subject {
+                    subject.validate(&scope).map_err(context)?;
+                }
+                body.validate(&scope).map_err(context)?;
+            }
+
+            if let Some(FailureAction::Notification { channel, message }) = &task.on_failure {
+                self.channel(channel).map_err(context)?;
+                let scope = TemplateScope { has_error: true, ..scope };
+                Template::parse(message)
+                    .and_then(|t| t.validate(&scope))
+                    .map_err(context)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn input_string<'a>(task: &'a Task, key: &str) -> AutomationResult<Option<&'a str>> {
+    match task.config.inputs.get(key) {
+        None => Ok(None),
+        Some(Value::String(s)) => Ok(Some(s)),
+        Some(_) => Err(AutomationError::Validation(format!("Input '{}' must be a string", key))),
+    }
+}
+
+fn message_templates(task: &Task) -> AutomationResult<(Option<Template>, Template)> {
+    let body = input_string(task, "message")?
+        .ok_or_else(|| AutomationError::Validation("Notification task requires a 'message' input".into()))?;
+    let subject = input_string(task, "subject")?.map(Template::parse).transpose()?;
+    Ok((subject, Template::parse(body)?))
+}
+
+// Every task reachable through dependencies has finished, so its outputs are in scope
+fn upstream_tasks(workflow: &Workflow, task_id: &str) -> HashSet<String> {
+    let by_id: HashMap<&str, &Task> = workflow.tasks.iter().map(|t| (t.id.as_str(), t)).collect();
+    let mut seen = HashSet::new();
+    let mut pending = vec![task_id];
+    while let Some(id) = pending.pop() {
+        for dependency in by_id.get(id).map(|t| t.dependencies.as_slice()).unwrap_or_default() {
+            if seen.insert(dependency.task_id.clone()) {
+                pending.push(&dependency.task_id);
+            }
+        }
+    }
+    seen
+}
+
+fn render_context(context: &ExecutionContext) -> RenderContext {
+    RenderContext {
+        variables: context.variables.clone(),
+        outputs: context
+            .previous_results
+            .iter()
+            .map(|(task_id, result)| (task_id.clone(), result.outputs.clone()))
+            .collect(),
+        run: HashMap::from([
+            ("id".to_string(), context.workflow_run_id.clone()),
+            ("task_run_id".to_string(), context.task_run_id.clone()),
+        ]),
+        error: None,
+    }
+}
+
+pub struct NotificationTaskExecutor {
+    sender: Arc<NotificationSender>,
+}
+
+impl NotificationTaskExecutor {
+    pub fn new(sender: Arc<NotificationSender>) -> Self {
+        Self { sender }
+    }
+}
+
+#[async_trait]
+impl TaskExecutor for NotificationTaskExecutor {
+    async fn execute_task(&self, task: Task, context: ExecutionContext) -> AutomationResult<TaskResult> {
+        let TaskType::Notification { channel } = &task.task_type else {
+            return Err(AutomationError::Task(format!("Task {} is not a notification task", task.id)));
+        };
+        let started = Instant::now();
+        let render_context = render_context(&context);
+        let (subject, body) = message_templates(&task)?;
+        let message = RenderedMessage {
+            subject: subject.map(|t| t.render(&render_context)).transpose()?,
+            body: body.render(&render_context)?,
+        };
+        self.sender.send(channel, &message).await?;
+
+        Ok(TaskResult {
+            status: RunStatus::Succeeded,
+            outputs: HashMap::from([("channel".to_string(), Value::String(channel.clone()))]),
+            error: None,
+            metrics: TaskMetrics {
+                duration_seconds: started.elapsed().as_secs() as i64,
+                retry_count: 0,
+                resource_usage: ResourceUsage {
+                    cpu_seconds: 0.0,
+                    memory_mb_seconds: 0.0,
+                    io_bytes: 0,
+                },
+            },
+        })
+    }
+
+    async fn validate_task(&self, task: &Task) -> AutomationResult<()> {
+        let TaskType::Notification { channel } = &task.task_type else {
+            return Err(AutomationError::Validation(format!("Task {} is not a notification task", task.id)));
+        };
+        self.sender.channel(channel)?;
+        message_templates(task).map(|_| ())
+    }
+
+    async fn abort_task(&self, _task_run_id: &str) -> AutomationResult<()> {
+        // Deliveries are short-lived and not cancellable once handed to the transport
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workflow::{ResourceRequirements, TaskConfig, TaskDependency, DependencyType, Variable, VariableType, WorkflowStatus};
+    use chrono::Utc;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    struct StaticSecrets(HashMap<String, String>);
+
+    #[async_trait]
+    impl SecretResolver for StaticSecrets {
+        async fn resolve(&self, reference: &str) -> AutomationResult<String> {
+            self.0
+                .get(reference)
+                .cloned()
+                .ok_or_else(|| AutomationError::Config(format!("Secret {} not found", reference)))
+        }
+    }
+
+    // Minimal HTTP server; replies with the queued statuses in order, then 200
+    async fn mock_webhook(statuses: Vec<u16>) -> (String, Arc<Mutex<Vec<serde_json::Value>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let statuses = Arc::new(Mutex::new(VecDeque::from(statuses)));
+
+        let bodies = received.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let mut reader = BufReader::new(stream);
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).await.unwrap();
+                    if line == "\r\n" || line.is_empty() {
+                        break;
+                    }
+                    if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).await.unwrap();
+                bodies.lock().await.push(serde_json::from_slice(&body).unwrap());
+
+                let status = statuses.lock().await.pop_front().unwrap_or(200);
+                let response = format!(
+                    "HTTP/1.1 {} Mock\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok",
+                    status
+                );
+                reader.get_mut().write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        (url, received)
+    }
+
+    // Minimal SMTP server accepting AUTH PLAIN; records the auth line and the DATA section
+    async fn mock_smtp() -> (u16, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let received = Arc::new(Mutex::new(Vec::new()));
+
+        let sessions = received.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let (read, mut write) = stream.into_split();
+                let mut reader = BufReader::new(read);
+                write.write_all(b"220 mock ESMTP\r\n").await.unwrap();
+                let mut transcript = String::new();
+                let mut in_data = false;
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).await.unwrap() == 0 {
+                        break;
+                    }
+                    if in_data {
+                        if line == ".\r\n" {
+                            in_data = false;
+                            write.write_all(b"250 queued\r\n").await.unwrap();
+                        } else {
+                            transcript.push_str(&line);
+                        }
+                        continue;
+                    }
+                    let command = line.to_ascii_uppercase();
+                    let reply: &[u8] = if command.starts_with("EHLO") {
+                        b"250-mock\r\n250 AUTH PLAIN\r\n"
+                    } else if command.starts_with("AUTH") {
+                        transcript.push_str(&line);
+                        b"235 accepted\r\n"
+                    } else if command.starts_with("DATA") {
+                        in_data = true;
+                        b"354 go ahead\r\n"
+                    } else if command.starts_with("QUIT") {
+                        write.write_all(b"221 bye\r\n").await.unwrap();
+                        break;
+                    } else {
+                        b"250 ok\r\n"
+                    };
+                    write.write_all(reply).await.unwrap();
+                }
+                sessions.lock().await.push(transcript);
+            }
+        });
+        (port, received)
+    }
+
+    fn secrets(entries: &[(&str, &str)]) -> Arc<dyn SecretResolver> {
+        Arc::new(StaticSecrets(
+            entries.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+        ))
+    }
+
+    fn fast_retry() -> RetryPolicy {
+        RetryPolicy::new(3).with_backoff(Duration::from_millis(1), Duration::from_millis(5))
+    }
+
+    fn task(id: &str, task_type: TaskType, inputs: &[(&str, &str)], dependencies: &[&str]) -> Task {
+        Task {
+            id: id.to_string(),
+            name: id.to_string(),
+            task_type,
+            config: TaskConfig {
+                inputs: inputs
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), Value::String(v.to_string())))
+                    .collect(),
+                environment: HashMap::new(),
+                resources: ResourceRequirements {
+                    cpu: "100m".into(),
+                    memory: "64Mi".into(),
+                    storage: None,
+                    gpu: None,
+                },
+                secrets: Vec::new(),
+                artifacts: Vec::new(),
+            },
+            dependencies: dependencies
+                .iter()
+                .map(|d| TaskDependency {
+                    task_id: d.to_string(),
+                    type_: DependencyType::Success,
+                    condition: None,
+                })
+                .collect(),
+            retry_policy: None,
+            timeout: None,
+            on_failure: None,
+            conditions: Vec::new(),
+        }
+    }
+
+    fn context(variables: &[(&str, &str)], outputs: &[(&str, &str, &str)]) -> ExecutionContext {
+        let mut previous_results: HashMap<String, TaskResult> = HashMap::new();
+        for (task_id, key, value) in outputs {
+            previous_results
+                .entry(task_id.to_string())
+                .or_insert_with(|| TaskResult {
+                    status: RunStatus::Succeeded,
+                    outputs: HashMap::new(),
+                    error: None,
+                    metrics: TaskMetrics {
+                        duration_seconds: 0,
+                        retry_count: 0,
+                        resource_usage: ResourceUsage {
+                            cpu_seconds: 0.0,
+                            memory_mb_seconds: 0.0,
+                            io_bytes: 0,
+                        },
+                    },
+                })
+                .outputs
+                .insert(key.to_string(), Value::String(value.to_string()));
+        }
+        ExecutionContext {
+            workflow_run_id: "run-42".into(),
+            task_run_id: "task-run-1".into(),
+            variables: variables
+                .iter()
+                .map(|(k, v)| (k.to_string(), Value::String(v.to_string())))
+                .collect(),
+            previous_results,
+            working_dir: std::env::temp_dir(),
+        }
+    }
+
+    fn notify_task() -> Task {
+        task(
+            "notify",
+            TaskType::Notification { channel: "ops".into() },
+            &[("message", "Deployed {{ outputs.build.version }} to {{ variables.env }} in {{ run.id }}")],
+            &["build"],
+        )
+    }
+
+    #[tokio::test]
+    async fn test_slack_task_renders_template() {
+        let (url, received) = mock_webhook(vec![]).await;
+        let sender = NotificationSender::new(secrets(&[("slack-ops-webhook", &url)]))
+            .with_channel(NotificationChannel::slack(
+                "ops",
+                SlackConfig::new("slack-ops-webhook").with_channel("#deploys"),
+            ));
+        let executor = NotificationTaskExecutor::new(Arc::new(sender));
+
+        let result = executor
+            .execute_task(notify_task(), context(&[("env", "prod")], &[("build", "version", "1.4.2")]))
+            .await
+            .unwrap();
+
+        assert!(matches!(result.status, RunStatus::Succeeded));
+        let bodies = received.lock().await;
+        assert_eq!(bodies.len(), 1);
+        assert_eq!(bodies[0]["text"], "Deployed 1.4.2 to prod in run-42");
+        assert_eq!(bodies[0]["channel"], "#deploys");
+    }
+
+    #[tokio::test]
+    async fn test_on_failure_sends_email_through_shared_sender() {
+        let (port, received) = mock_smtp().await;
+        let sender = NotificationSender::new(secrets(&[("smtp-password", "hunter2")])).with_channel(
+            NotificationChannel::email(
+                "oncall",
+                EmailConfig::new("127.0.0.1", "nexus@example.com", vec!["oncall@example.com".into()])
+                    .with_port(port)
+                    .with_starttls(false)
+                    .with_credentials("nexus", "smtp-password"),
+            ),
+        );
+
+        let mut failing = task("migrate", TaskType::Database { operation: "migrate".into() }, &[], &[]);
+        failing.on_failure = Some(FailureAction::Notification {
+            channel: "oncall".into(),
+            message: "{{ error.code }}: {{ error.message }} ({{ variables.env }})".into(),
+        });
+        let error = TaskError {
+            code: "E_LOCK".into(),
+            message: "lock timeout".into(),
+            details: None,
+            retry_count: 2,
+        };
+
+        let sent = sender
+            .notify_failure(&failing, &context(&[("env", "staging")], &[]), &error)
+            .await
+            .unwrap();
+
+        assert!(sent);
+        let sessions = received.lock().await;
+        assert_eq!(sessions.len(), 1);
+        // AUTH PLAIN carries base64("\0nexus\0hunter2")
+        assert!(sessions[0].contains("AUTH PLAIN AG5leHVzAGh1bnRlcjI="));
+        assert!(sessions[0].contains("Subject: Task migrate failed"));
+        assert!(sessions[0].contains("E_LOCK: lock timeout (staging)"));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_rejects_excess_messages() {
+        let (url, received) = mock_webhook(vec![]).await;
+        let sender = NotificationSender::new(secrets(&[("hook", &url)]))
+            .with_channel(NotificationChannel::slack("ops", SlackConfig::new("hook")).with_rate_limit(2, 60));
+        let message = RenderedMessage {
+            subject: None,
+            body: "ping".into(),
+        };
+
+        sender.send("ops", &message).await.unwrap();
+        sender.send("ops", &message).await.unwrap();
+        let err = sender.send("ops", &message).await.unwrap_err();
+
+        assert!(matches!(err, AutomationError::Throttled(_)));
+        assert_eq!(received.lock().await.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_transient_webhook_failures_are_retried() {
+        let (url, received) = mock_webhook(vec![503]).await;
+        let sender = NotificationSender::new(secrets(&[("hook", &url)]))
+            .with_channel(NotificationChannel::slack("ops", SlackConfig::new("hook")))
+            .with_retry_policy(fast_retry());
+        let message = RenderedMessage {
+            subject: None,
+            body: "ping".into(),
+        };
+
+        sender.send("ops", &message).await.unwrap();
+        assert_eq!(received.lock().await.len(), 2);
+
+        let (url, received) = mock_webhook(vec![400]).await;
+        let sender = NotificationSender::new(secrets(&[("hook", &url)]))
+            .with_channel(NotificationChannel::slack("ops", SlackConfig::new("hook")))
+            .with_retry_policy(fast_retry());
+        assert!(sender.send("ops", &message).await.is_err());
+        assert_eq!(received.lock().await.len(), 1);
+    }
+
+    #[test]
+    fn test_validate_workflow_reports_missing_variables() {
+        let sender = NotificationSender::new(secrets(&[]))
+            .with_channel(NotificationChannel::slack("ops", SlackConfig::new("hook")));
+        let build = task("build", TaskType::Script { runtime: "bash".into() }, &[], &[]);
+        let mut workflow = Workflow {
+            id: "wf".into(),
+            name: "deploy".into(),
+            description: String::new(),
+            version: "1".into(),
+            tasks: vec![build, notify_task()],
+            triggers: Vec::new(),
+            status: WorkflowStatus::Active,
+            schedule: None,
+            variables: HashMap::new(),
+            timeout: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            metadata: HashMap::new(),
+        };
+
+        let err = sender.validate_workflow(&workflow).unwrap_err().to_string();
+        assert!(err.contains("variables.env"), "{}", err);
+
+        workflow.variables.insert(
+            "env".into(),
+            Variable {
+                type_: VariableType::String,
+                value: None,
+                default: Some(Value::String("dev".into())),
+                description: None,
+                required: false,
+            },
+        );
+        sender.validate_workflow(&workflow).unwrap();
+
+        workflow.tasks[0].on_failure = Some(FailureAction::Notification {
+            channel: "pager".into(),
+            message: "{{ error.message }}".into(),
+        });
+        let err = sender.validate_workflow(&workflow).unwrap_err().to_string();
+        assert!(err.contains("pager"), "{}", err);
+    }
+}
@@ -0,0 +1,77 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AutomationError, AutomationResult};
+use super::RenderedMessage;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SlackConfig {
+    // Key vault secret holding the incoming webhook URL
+    pub webhook_secret: String,
+    pub channel: Option<String>,
+    pub username: Option<String>,
+}
+
+impl SlackConfig {
+    pub fn new(webhook_secret: impl Into<String>) -> Self {
+        Self {
+            webhook_secret: webhook_secret.into(),
+            channel: None,
+            username: None,
+        }
+    }
+
+    pub fn with_channel(mut self, channel: impl Into<String>) -> Self {
+        self.channel = Some(channel.into());
+        self
+    }
+
+    pub fn with_username(mut self, username: impl Into<String>) -> Self {
+        self.username = Some(username.into());
+        self
+    }
+}
+
+fn payload(config: &SlackConfig, message: &RenderedMessage) -> serde_json::Value {
+    let text = match &message.subject {
+        Some(subject) => format!("*{}*\n{}", subject, message.body),
+        None => message.body.clone(),
+    };
+    let mut payload = serde_json::json!({ "text": text });
+    if let Some(channel) = &config.channel {
+        payload["channel"] = channel.clone().into();
+    }
+    if let Some(username) = &config.username {
+        payload["username"] = username.clone().into();
+    }
+    payload
+}
+
+pub(crate) async fn send(
+    client: &reqwest::Client,
+    webhook_url: &str,
+    config: &SlackConfig,
+    message: &RenderedMessage,
+) -> AutomationResult<()> {
+    let response = client
+        .post(webhook_url)
+        .json(&payload(config, message))
+        .send()
+        .await
+        .map_err(|e| {
+            if e.is_connect() || e.is_timeout() {
+                AutomationError::Unavailable(format!("Slack webhook unreachable: {}", e))
+            } else {
+                AutomationError::Notification(format!("Slack webhook request failed: {}", e))
+            }
+        })?;
+
+    let status = response.status();
+    if status.is_success() {
+        return Ok(());
+    }
+    let body = response.text().await.unwrap_or_default();
+    Err(AutomationError::from_http_status(
+        status.as_u16(),
+        format!("Slack webhook returned {}: {}", status, body),
+    ))
+}
@@ -0,0 +1,213 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use crate::error::{AutomationError, AutomationResult};
+use crate::trigger::value_to_string;
+use crate::workflow::{TaskError, Value};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Literal(String),
+    Placeholder(Reference),
+}
+
+// A `{{ ... }}` placeholder; only these four roots are addressable
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Reference {
+    Variable(String),
+    Output { task_id: String, key: String },
+    Run(String),
+    Error(String),
+}
+
+impl std::fmt::Display for Reference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Reference::Variable(name) => write!(f, "variables.{}", name),
+            Reference::Output { task_id, key } => write!(f, "outputs.{}.{}", task_id, key),
+            Reference::Run(field) => write!(f, "run.{}", field),
+            Reference::Error(field) => write!(f, "error.{}", field),
+        }
+    }
+}
+
+const RUN_FIELDS: &[&str] = &["id", "task_run_id"];
+const ERROR_FIELDS: &[&str] = &["code", "message", "retry_count"];
+
+impl Reference {
+    fn parse(expr: &str) -> AutomationResult<Self> {
+        let parts: Vec<&str> = expr.split('.').collect();
+        let invalid = || AutomationError::Validation(format!("Invalid template reference '{}'", expr));
+        if parts.iter().any(|p| p.is_empty()) {
+            return Err(invalid());
+        }
+        match parts.as_slice() {
+            ["variables", name] => Ok(Reference::Variable(name.to_string())),
+            ["outputs", task_id, key] => Ok(Reference::Output {
+                task_id: task_id.to_string(),
+                key: key.to_string(),
+            }),
+            ["run", field] if RUN_FIELDS.contains(field) => Ok(Reference::Run(field.to_string())),
+            ["error", field] if ERROR_FIELDS.contains(field) => Ok(Reference::Error(field.to_string())),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    segments: Vec<Segment>,
+}
+
+impl Template {
+    pub fn parse(source: &str) -> AutomationResult<Self> {
+        let mut segments = Vec::new();
+        let mut rest = source;
+        while let Some(start) = rest.find("{{") {
+            if start > 0 {
+                segments.push(Segment::Literal(rest[..start].to_string()));
+            }
+            let after = &rest[start + 2..];
+            let end = after.find("}}").ok_or_else(|| {
+                AutomationError::Validation(format!("Unterminated placeholder in template '{}'", source))
+            })?;
+            segments.push(Segment::Placeholder(Reference::parse(after[..end].trim())?));
+            rest = &after[end + 2..];
+        }
+        if !rest.is_empty() {
+            segments.push(Segment::Literal(rest.to_string()));
+        }
+        Ok(Self { segments })
+    }
+
+    pub fn references(&self) -> impl Iterator<Item = &Reference> {
+        self.segments.iter().filter_map(|s| match s {
+            Segment::Placeholder(r) => Some(r),
+            Segment::Literal(_) => None,
+        })
+    }
+
+    // Checks every reference against what will be in scope when the template renders
+    pub fn validate(&self, scope: &TemplateScope) -> AutomationResult<()> {
+        let missing: BTreeSet<String> = self
+            .references()
+            .filter(|r| !scope.contains(r))
+            .map(|r| r.to_string())
+            .collect();
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(AutomationError::Validation(format!(
+                "Template references unknown values: {}",
+                missing.into_iter().collect::<Vec<_>>().join(", ")
+            )))
+        }
+    }
+
+    pub fn render(&self, context: &RenderContext) -> AutomationResult<String> {
+        let mut out = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(text) => out.push_str(text),
+                Segment::Placeholder(reference) => {
+                    let value = context.lookup(reference).ok_or_else(|| {
+                        AutomationError::Notification(format!("No value for template reference '{}'", reference))
+                    })?;
+                    out.push_str(&value);
+                }
+            }
+        }
+        Ok(out)
+    }
+}
+
+// Names a template may reference, derived from the workflow definition at validation time
+#[derive(Debug, Clone, Default)]
+pub struct TemplateScope {
+    pub variables: HashSet<String>,
+    pub upstream_tasks: HashSet<String>,
+    pub has_error: bool,
+}
+
+impl TemplateScope {
+    fn contains(&self, reference: &Reference) -> bool {
+        match reference {
+            Reference::Variable(name) => self.variables.contains(name),
+            // Output keys are only known at runtime, so only the producing task is checked
+            Reference::Output { task_id, .. } => self.upstream_tasks.contains(task_id),
+            Reference::Run(_) => true,
+            Reference::Error(_) => self.has_error,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RenderContext {
+    pub variables: HashMap<String, Value>,
+    pub outputs: HashMap<String, HashMap<String, Value>>,
+    pub run: HashMap<String, String>,
+    pub error: Option<TaskError>,
+}
+
+impl RenderContext {
+    fn lookup(&self, reference: &Reference) -> Option<String> {
+        match reference {
+            Reference::Variable(name) => self.variables.get(name).map(value_to_string),
+            Reference::Output { task_id, key } => self
+                .outputs
+                .get(task_id)
+                .and_then(|outputs| outputs.get(key))
+                .map(value_to_string),
+            Reference::Run(field) => self.run.get(field).cloned(),
+            Reference::Error(field) => self.error.as_ref().and_then(|e| match field.as_str() {
+                "code" => Some(e.code.clone()),
+                "message" => Some(e.message.clone()),
+                "retry_count" => Some(e.retry_count.to_string()),
+                _ => None,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_render() {
+        let template = Template::parse("Deploy {{ variables.env }} by {{outputs.build.sha}} ({{ run.id }})").unwrap();
+
+        let mut context = RenderContext::default();
+        context.variables.insert("env".into(), Value::String("prod".into()));
+        context.outputs.insert(
+            "build".into(),
+            HashMap::from([("sha".to_string(), Value::String("abc123".into()))]),
+        );
+        context.run.insert("id".into(), "run-1".into());
+
+        assert_eq!(template.render(&context).unwrap(), "Deploy prod by abc123 (run-1)");
+    }
+
+    #[test]
+    fn test_validate_reports_missing_references() {
+        let template = Template::parse("{{ variables.env }} {{ variables.region }} {{ outputs.test.report }} {{ error.message }}").unwrap();
+        let scope = TemplateScope {
+            variables: HashSet::from(["env".to_string()]),
+            upstream_tasks: HashSet::from(["build".to_string()]),
+            has_error: false,
+        };
+
+        let err = template.validate(&scope).unwrap_err().to_string();
+        assert!(err.contains("error.message"));
+        assert!(err.contains("outputs.test.report"));
+        assert!(err.contains("variables.region"));
+        assert!(!err.contains("variables.env"));
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed_placeholders() {
+        assert!(Template::parse("{{ variables.env ").is_err());
+        assert!(Template::parse("{{ secrets.token }}").is_err());
+        assert!(Template::parse("{{ outputs.build }}").is_err());
+        assert!(Template::parse("no placeholders").is_ok());
+    }
+}
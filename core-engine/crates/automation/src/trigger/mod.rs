@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+
+use crate::workflow::{EventFilter, FilterOperator, Value};
+
+pub mod queue;
+
+pub use queue::{QueueTriggerConfig, QueueTriggerRunner, PollSummary};
+
+// Event filters are ANDed together; an empty filter list matches everything
+pub fn matches_filters(filters: &[EventFilter], fields: &HashMap<String, String>) -> bool {
+    filters.iter().all(|filter| matches_filter(filter, fields.get(&filter.field)))
+}
+
+fn matches_filter(filter: &EventFilter, actual: Option<&String>) -> bool {
+    if let FilterOperator::Exists = filter.operator {
+        return actual.is_some();
+    }
+    let Some(actual) = actual else {
+        return matches!(filter.operator, FilterOperator::NotEquals);
+    };
+    let expected = value_to_string(&filter.value);
+
+    match filter.operator {
+        FilterOperator::Equals => *actual == expected,
+        FilterOperator::NotEquals => *actual != expected,
+        FilterOperator::Contains => actual.contains(&expected),
+        FilterOperator::StartsWith => actual.starts_with(&expected),
+        FilterOperator::EndsWith => actual.ends_with(&expected),
+        FilterOperator::GreaterThan | FilterOperator::LessThan => {
+            match (actual.parse::<f64>(), expected.parse::<f64>()) {
+                (Ok(a), Ok(e)) if matches!(filter.operator, FilterOperator::GreaterThan) => a > e,
+                (Ok(a), Ok(e)) => a < e,
+                _ => false,
+            }
+        }
+        FilterOperator::Exists => unreachable!(),
+    }
+}
+
+pub fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::String(s) | Value::Reference(s) => s.clone(),
+        Value::Integer(i) => i.to_string(),
+        Value::Float(f) => f.to_string(),
+        Value::Boolean(b) => b.to_string(),
+        Value::Array(_) | Value::Object(_) => serde_json::to_string(&value_to_json(value)).unwrap_or_default(),
+    }
+}
+
+pub fn json_to_value(json: serde_json::Value) -> Value {
+    match json {
+        serde_json::Value::Null => Value::String(String::new()),
+        serde_json::Value::Bool(b) => Value::Boolean(b),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(i) => Value::Integer(i),
+            None => Value::Float(n.as_f64().unwrap_or_default()),
+        },
+        serde_json::Value::String(s) => Value::String(s),
+        serde_json::Value::Array(items) => Value::Array(items.into_iter().map(json_to_value).collect()),
+        serde_json::Value::Object(map) => {
+            Value::Object(map.into_iter().map(|(k, v)| (k, json_to_value(v))).collect())
+        }
+    }
+}
+
+pub fn value_to_json(value: &Value) -> serde_json::Value {
+    match value {
+        Value::String(s) | Value::Reference(s) => serde_json::Value::String(s.clone()),
+        Value::Integer(i) => serde_json::json!(i),
+        Value::Float(f) => serde_json::json!(f),
+        Value::Boolean(b) => serde_json::Value::Bool(*b),
+        Value::Array(items) => serde_json::Value::Array(items.iter().map(value_to_json).collect()),
+        Value::Object(map) => serde_json::Value::Object(
+            map.iter().map(|(k, v)| (k.clone(), value_to_json(v))).collect(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(field: &str, operator: FilterOperator, value: Value) -> EventFilter {
+        EventFilter {
+            field: field.to_string(),
+            operator,
+            value,
+        }
+    }
+
+    #[test]
+    fn test_filters() {
+        let mut fields = HashMap::new();
+        fields.insert("event".to_string(), "order.created".to_string());
+        fields.insert("amount".to_string(), "250".to_string());
+
+        assert!(matches_filters(&[], &fields));
+        assert!(matches_filters(
+            &[
+                filter("event", FilterOperator::StartsWith, Value::String("order.".into())),
+                filter("amount", FilterOperator::GreaterThan, Value::Integer(100)),
+                filter("region", FilterOperator::NotEquals, Value::String("eu".into())),
+            ],
+            &fields
+        ));
+        assert!(!matches_filters(
+            &[filter("amount", FilterOperator::LessThan, Value::Float(99.5))],
+            &fields
+        ));
+        assert!(!matches_filters(
+            &[filter("region", FilterOperator::Exists, Value::Boolean(true))],
+            &fields
+        ));
+    }
+}
@@ -0,0 +1,543 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use tokio::sync::{watch, Semaphore};
+use tokio::task::JoinSet;
+use tracing::{debug, info, warn};
+
+use sirsi_data_services::queue::{Message, MessageOperations, QueueManager};
+
+use crate::error::{AutomationError, AutomationResult};
+use crate::workflow::{Trigger, TriggerType, Value, WorkflowManager};
+use super::{json_to_value, matches_filters};
+
+#[derive(Debug, Clone)]
+pub struct QueueTriggerConfig {
+    pub max_in_flight: usize,
+    pub batch_size: i32,
+    pub wait_time_seconds: i32,
+    pub max_delivery_attempts: i32,
+    pub dead_letter_queue: Option<String>,
+}
+
+impl Default for QueueTriggerConfig {
+    fn default() -> Self {
+        Self {
+            max_in_flight: 10,
+            batch_size: 10,
+            wait_time_seconds: 20,
+            max_delivery_attempts: 5,
+            dead_letter_queue: None,
+        }
+    }
+}
+
+impl QueueTriggerConfig {
+    // Reads overrides from the trigger settings, falling back to the defaults
+    pub fn from_trigger(trigger: &Trigger) -> AutomationResult<Self> {
+        let mut config = Self::default();
+        let settings = &trigger.config.settings;
+
+        fn parse<T: std::str::FromStr>(settings: &HashMap<String, String>, key: &str) -> AutomationResult<Option<T>> {
+            settings
+                .get(key)
+                .map(|v| {
+                    v.parse::<T>()
+                        .map_err(|_| AutomationError::Config(format!("Invalid value for {}: {}", key, v)))
+                })
+                .transpose()
+        }
+
+        if let Some(v) = parse(settings, "max_in_flight")? {
+            config.max_in_flight = v;
+        }
+        if let Some(v) = parse(settings, "batch_size")? {
+            config.batch_size = v;
+        }
+        if let Some(v) = parse(settings, "wait_time_seconds")? {
+            config.wait_time_seconds = v;
+        }
+        if let Some(v) = parse(settings, "max_delivery_attempts")? {
+            config.max_delivery_attempts = v;
+        }
+        config.dead_letter_queue = settings.get("dead_letter_queue").cloned();
+
+        if config.max_in_flight == 0 {
+            return Err(AutomationError::Config("max_in_flight must be at least 1".into()));
+        }
+        Ok(config)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PollSummary {
+    pub received: usize,
+    pub started: usize,
+    pub filtered: usize,
+    pub failed: usize,
+    pub dead_lettered: usize,
+}
+
+enum Disposition {
+    Started,
+    Filtered,
+    Failed,
+    DeadLettered,
+}
+
+pub struct QueueTriggerRunner {
+    trigger: Trigger,
+    workflow_id: String,
+    queue_id: String,
+    messages: Arc<dyn MessageOperations>,
+    workflows: Arc<dyn WorkflowManager>,
+    config: QueueTriggerConfig,
+    permits: Arc<Semaphore>,
+}
+
+impl QueueTriggerRunner {
+    pub fn new(
+        trigger: Trigger,
+        workflow_id: String,
+        messages: Arc<dyn MessageOperations>,
+        workflows: Arc<dyn WorkflowManager>,
+        config: QueueTriggerConfig,
+    ) -> AutomationResult<Self> {
+        if !matches!(trigger.type_, TriggerType::Queue) {
+            return Err(AutomationError::Validation(format!("Trigger {} is not a queue trigger", trigger.id)));
+        }
+        if trigger.config.source.is_empty() {
+            return Err(AutomationError::Validation(format!("Trigger {} has no source queue", trigger.id)));
+        }
+
+        Ok(Self {
+            queue_id: trigger.config.source.clone(),
+            permits: Arc::new(Semaphore::new(config.max_in_flight)),
+            trigger,
+            workflow_id,
+            messages,
+            workflows,
+            config,
+        })
+    }
+
+    // Falls back to the dead letter queue configured on the source queue itself
+    pub async fn resolve_dead_letter_queue(&mut self, queues: &dyn QueueManager) -> AutomationResult<()> {
+        if self.config.dead_letter_queue.is_none() {
+            let queue = queues.get_queue(&self.queue_id).await?;
+            self.config.dead_letter_queue = queue.config.dead_letter_queue;
+        }
+        Ok(())
+    }
+
+    pub async fn run(self: Arc<Self>, mut shutdown: watch::Receiver<bool>) -> AutomationResult<()> {
+        info!("Queue trigger {} listening on {}", self.trigger.id, self.queue_id);
+        loop {
+            tokio::select! {
+                result = self.poll_once() => {
+                    if let Err(e) = result {
+                        warn!("Queue trigger {} poll failed: {}", self.trigger.id, e);
+                        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+                    }
+                }
+                _ = shutdown.changed() => {
+                    info!("Queue trigger {} stopping", self.trigger.id);
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    pub async fn poll_once(&self) -> AutomationResult<PollSummary> {
+        let batch = self
+            .messages
+            .receive_messages(&self.queue_id, self.config.batch_size, self.config.wait_time_seconds)
+            .await?;
+
+        let mut summary = PollSummary {
+            received: batch.len(),
+            ..Default::default()
+        };
+        if !self.trigger.enabled {
+            return Ok(summary);
+        }
+
+        let mut handlers = JoinSet::new();
+        for message in batch {
+            let permit = self
+                .permits
+                .clone()
+                .acquire_owned()
+                .await
+                .map_err(|e| AutomationError::Internal(e.to_string()))?;
+            let handler = MessageHandler {
+                trigger_id: self.trigger.id.clone(),
+                filters: self.trigger.filters.clone(),
+                workflow_id: self.workflow_id.clone(),
+                queue_id: self.queue_id.clone(),
+                messages: self.messages.clone(),
+                workflows: self.workflows.clone(),
+                max_delivery_attempts: self.config.max_delivery_attempts,
+                dead_letter_queue: self.config.dead_letter_queue.clone(),
+            };
+            handlers.spawn(async move {
+                let result = handler.handle(message).await;
+                drop(permit);
+                result
+            });
+        }
+
+        while let Some(result) = handlers.join_next().await {
+            match result {
+                Ok(Ok(Disposition::Started)) => summary.started += 1,
+                Ok(Ok(Disposition::Filtered)) => summary.filtered += 1,
+                Ok(Ok(Disposition::Failed)) => summary.failed += 1,
+                Ok(Ok(Disposition::DeadLettered)) => summary.dead_lettered += 1,
+                Ok(Err(e)) => {
+                    warn!("Queue trigger {} failed to settle message: {}", self.trigger.id, e);
+                    summary.failed += 1;
+                }
+                Err(e) => {
+                    warn!("Queue trigger {} handler panicked: {}", self.trigger.id, e);
+                    summary.failed += 1;
+                }
+            }
+        }
+
+        Ok(summary)
+    }
+}
+
+struct MessageHandler {
+    trigger_id: String,
+    filters: Vec<crate::workflow::EventFilter>,
+    workflow_id: String,
+    queue_id: String,
+    messages: Arc<dyn MessageOperations>,
+    workflows: Arc<dyn WorkflowManager>,
+    max_delivery_attempts: i32,
+    dead_letter_queue: Option<String>,
+}
+
+impl MessageHandler {
+    async fn handle(&self, message: Message) -> AutomationResult<Disposition> {
+        if !matches_filters(&self.filters, &message.attributes) {
+            debug!("Message {} does not match trigger {} filters", message.id, self.trigger_id);
+            self.messages.delete_message(&self.queue_id, &message.id).await?;
+            return Ok(Disposition::Filtered);
+        }
+
+        // The key makes redelivered messages resolve to the run created on first delivery
+        let idempotency_key = format!("{}:{}", self.trigger_id, message.id);
+        match self
+            .workflows
+            .start_workflow_with_key(&self.workflow_id, message_inputs(&message), &idempotency_key)
+            .await
+        {
+            Ok(run) => {
+                debug!("Message {} started run {}", message.id, run.id);
+                self.messages.delete_message(&self.queue_id, &message.id).await?;
+                Ok(Disposition::Started)
+            }
+            Err(e) if message.delivery_count >= self.max_delivery_attempts => {
+                let Some(dlq) = &self.dead_letter_queue else {
+                    warn!(
+                        "Message {} failed {} times and no dead letter queue is configured: {}",
+                        message.id, message.delivery_count, e
+                    );
+                    return Ok(Disposition::Failed);
+                };
+                warn!("Routing message {} to dead letter queue {}: {}", message.id, dlq, e);
+                let mut poisoned = message.clone();
+                poisoned
+                    .attributes
+                    .insert("sirsi-dlq-reason".to_string(), e.to_string());
+                poisoned
+                    .attributes
+                    .insert("sirsi-source-queue".to_string(), self.queue_id.clone());
+                self.messages.send_message(dlq, poisoned).await?;
+                self.messages.delete_message(&self.queue_id, &message.id).await?;
+                Ok(Disposition::DeadLettered)
+            }
+            Err(e) => {
+                // Left on the queue; it becomes visible again once the visibility timeout lapses
+                warn!(
+                    "Failed to start workflow {} for message {} (attempt {}): {}",
+                    self.workflow_id, message.id, message.delivery_count, e
+                );
+                Ok(Disposition::Failed)
+            }
+        }
+    }
+}
+
+fn message_inputs(message: &Message) -> HashMap<String, Value> {
+    let body = match serde_json::from_slice::<serde_json::Value>(&message.data) {
+        Ok(json) => json_to_value(json),
+        Err(_) => Value::String(String::from_utf8_lossy(&message.data).into_owned()),
+    };
+
+    let mut inputs = HashMap::new();
+    inputs.insert("message".to_string(), body);
+    inputs.insert("message_id".to_string(), Value::String(message.id.clone()));
+    inputs.insert(
+        "attributes".to_string(),
+        Value::Object(
+            message
+                .attributes
+                .iter()
+                .map(|(k, v)| (k.clone(), Value::String(v.clone())))
+                .collect(),
+        ),
+    );
+    if let Some(correlation_id) = &message.correlation_id {
+        inputs.insert("correlation_id".to_string(), Value::String(correlation_id.clone()));
+    }
+    inputs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workflow::{
+        EventFilter, FilterOperator, ResourceUsage, RunMetrics, RunStatus, RunTrigger, TriggerConfig, Workflow,
+        WorkflowRun,
+    };
+    use async_trait::async_trait;
+    use chrono::{DateTime, Utc};
+    use sirsi_data_services::queue::InMemoryQueueBackend;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    #[derive(Default)]
+    struct RecordingWorkflows {
+        runs: Mutex<HashMap<String, WorkflowRun>>,
+        starts: AtomicUsize,
+        fail: std::sync::atomic::AtomicBool,
+    }
+
+    #[async_trait]
+    impl WorkflowManager for RecordingWorkflows {
+        async fn create_workflow(&self, workflow: Workflow) -> AutomationResult<Workflow> {
+            Ok(workflow)
+        }
+        async fn update_workflow(&self, workflow: Workflow) -> AutomationResult<Workflow> {
+            Ok(workflow)
+        }
+        async fn delete_workflow(&self, _id: &str) -> AutomationResult<()> {
+            Ok(())
+        }
+        async fn get_workflow(&self, id: &str) -> AutomationResult<Workflow> {
+            Err(AutomationError::NotFound(id.to_string()))
+        }
+        async fn list_workflows(&self) -> AutomationResult<Vec<Workflow>> {
+            Ok(Vec::new())
+        }
+        async fn start_workflow(&self, id: &str, inputs: HashMap<String, Value>) -> AutomationResult<WorkflowRun> {
+            self.start_workflow_with_key(id, inputs, &uuid::Uuid::new_v4().to_string()).await
+        }
+        async fn start_workflow_with_key(
+            &self,
+            id: &str,
+            inputs: HashMap<String, Value>,
+            idempotency_key: &str,
+        ) -> AutomationResult<WorkflowRun> {
+            if self.fail.load(Ordering::SeqCst) {
+                return Err(AutomationError::Unavailable("scheduler offline".into()));
+            }
+            let mut runs = self.runs.lock().unwrap();
+            if let Some(run) = runs.get(idempotency_key) {
+                return Ok(run.clone());
+            }
+            self.starts.fetch_add(1, Ordering::SeqCst);
+            let run = WorkflowRun {
+                id: uuid::Uuid::new_v4().to_string(),
+                workflow_id: id.to_string(),
+                version: "1".into(),
+                status: RunStatus::Pending,
+                trigger: RunTrigger {
+                    type_: TriggerType::Queue,
+                    source: "jobs".into(),
+                    event: None,
+                },
+                task_runs: Vec::new(),
+                variables: inputs,
+                start_time: Utc::now(),
+                end_time: None,
+                metrics: RunMetrics {
+                    total_duration_seconds: 0,
+                    task_count: 0,
+                    failed_tasks: 0,
+                    retried_tasks: 0,
+                    resource_usage: ResourceUsage {
+                        cpu_seconds: 0.0,
+                        memory_mb_seconds: 0.0,
+                        io_bytes: 0,
+                    },
+                },
+            };
+            runs.insert(idempotency_key.to_string(), run.clone());
+            Ok(run)
+        }
+        async fn stop_workflow(&self, _run_id: &str) -> AutomationResult<()> {
+            Ok(())
+        }
+        async fn get_workflow_run(&self, run_id: &str) -> AutomationResult<WorkflowRun> {
+            Err(AutomationError::NotFound(run_id.to_string()))
+        }
+        async fn list_workflow_runs(&self, _workflow_id: &str) -> AutomationResult<Vec<WorkflowRun>> {
+            Ok(self.runs.lock().unwrap().values().cloned().collect())
+        }
+    }
+
+    // Loses the first delete so the message is redelivered after its visibility timeout
+    struct LossyDeletes {
+        inner: InMemoryQueueBackend,
+        dropped: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl MessageOperations for LossyDeletes {
+        async fn send_message(&self, queue_id: &str, message: Message) -> sirsi_data_services::DataResult<String> {
+            self.inner.send_message(queue_id, message).await
+        }
+        async fn send_batch(&self, queue_id: &str, messages: Vec<Message>) -> sirsi_data_services::DataResult<Vec<String>> {
+            self.inner.send_batch(queue_id, messages).await
+        }
+        async fn receive_messages(&self, queue_id: &str, max: i32, wait: i32) -> sirsi_data_services::DataResult<Vec<Message>> {
+            self.inner.receive_messages(queue_id, max, wait).await
+        }
+        async fn delete_message(&self, queue_id: &str, message_id: &str) -> sirsi_data_services::DataResult<()> {
+            if self.dropped.fetch_add(1, Ordering::SeqCst) == 0 {
+                return Err(sirsi_data_services::DataError::Unavailable("connection reset".into()));
+            }
+            self.inner.delete_message(queue_id, message_id).await
+        }
+        async fn peek_messages(&self, queue_id: &str, count: i32) -> sirsi_data_services::DataResult<Vec<Message>> {
+            self.inner.peek_messages(queue_id, count).await
+        }
+        async fn change_visibility(&self, queue_id: &str, message_id: &str, delay: chrono::Duration) -> sirsi_data_services::DataResult<()> {
+            self.inner.change_visibility(queue_id, message_id, delay).await
+        }
+        async fn send_scheduled(&self, queue_id: &str, message: Message, at: DateTime<Utc>) -> sirsi_data_services::DataResult<String> {
+            self.inner.send_scheduled(queue_id, message, at).await
+        }
+        async fn list_scheduled(&self, queue_id: &str) -> sirsi_data_services::DataResult<Vec<Message>> {
+            self.inner.list_scheduled(queue_id).await
+        }
+    }
+
+    fn trigger(filters: Vec<EventFilter>) -> Trigger {
+        Trigger {
+            id: "orders".into(),
+            type_: TriggerType::Queue,
+            config: TriggerConfig {
+                source: "jobs".into(),
+                settings: HashMap::new(),
+                auth: None,
+            },
+            filters,
+            enabled: true,
+        }
+    }
+
+    fn message(body: &str, kind: &str) -> Message {
+        let mut attributes = HashMap::new();
+        attributes.insert("kind".to_string(), kind.to_string());
+        Message {
+            id: String::new(),
+            queue_id: String::new(),
+            data: body.as_bytes().to_vec(),
+            attributes,
+            publish_time: Utc::now(),
+            delivery_count: 0,
+            scheduled_for: None,
+            correlation_id: None,
+            reply_to: None,
+        }
+    }
+
+    fn config() -> QueueTriggerConfig {
+        QueueTriggerConfig {
+            max_in_flight: 2,
+            batch_size: 10,
+            wait_time_seconds: 1,
+            max_delivery_attempts: 2,
+            dead_letter_queue: Some("jobs-dlq".into()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_redelivered_message_creates_one_run() {
+        let queue = Arc::new(LossyDeletes {
+            inner: InMemoryQueueBackend::new().with_visibility_timeout(Duration::from_millis(50)),
+            dropped: AtomicUsize::new(0),
+        });
+        let workflows = Arc::new(RecordingWorkflows::default());
+        queue.send_message("jobs", message(r#"{"order": 42}"#, "order")).await.unwrap();
+
+        let runner = QueueTriggerRunner::new(trigger(Vec::new()), "fulfil".into(), queue.clone(), workflows.clone(), config()).unwrap();
+
+        let first = runner.poll_once().await.unwrap();
+        assert_eq!(first.received, 1);
+        assert_eq!(first.failed, 1);
+
+        let second = runner.poll_once().await.unwrap();
+        assert_eq!(second.received, 1);
+        assert_eq!(second.started, 1);
+
+        assert_eq!(workflows.starts.load(Ordering::SeqCst), 1);
+        assert!(queue.peek_messages("jobs", 10).await.unwrap().is_empty());
+
+        let runs = workflows.list_workflow_runs("fulfil").await.unwrap();
+        match runs[0].variables.get("message") {
+            Some(Value::Object(body)) => assert!(matches!(body.get("order"), Some(Value::Integer(42)))),
+            other => panic!("unexpected message input: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_filters_and_dead_letter_routing() {
+        let queue = Arc::new(InMemoryQueueBackend::new().with_visibility_timeout(Duration::from_millis(20)));
+        let workflows = Arc::new(RecordingWorkflows::default());
+        workflows.fail.store(true, Ordering::SeqCst);
+
+        queue.send_message("jobs", message("ignored", "refund")).await.unwrap();
+        queue.send_message("jobs", message("poison", "order")).await.unwrap();
+
+        let filters = vec![EventFilter {
+            field: "kind".into(),
+            operator: FilterOperator::Equals,
+            value: Value::String("order".into()),
+        }];
+        let runner = QueueTriggerRunner::new(trigger(filters), "fulfil".into(), queue.clone(), workflows.clone(), config()).unwrap();
+
+        let first = runner.poll_once().await.unwrap();
+        assert_eq!(first.filtered, 1);
+        assert_eq!(first.failed, 1);
+
+        let second = runner.poll_once().await.unwrap();
+        assert_eq!(second.dead_lettered, 1);
+
+        assert!(queue.peek_messages("jobs", 10).await.unwrap().is_empty());
+        let dead = queue.peek_messages("jobs-dlq", 10).await.unwrap();
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0].data, b"poison");
+        assert!(dead[0].attributes.contains_key("sirsi-dlq-reason"));
+        assert_eq!(workflows.starts.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_config_from_trigger_settings() {
+        let mut t = trigger(Vec::new());
+        t.config.settings.insert("max_in_flight".into(), "4".into());
+        t.config.settings.insert("dead_letter_queue".into(), "dlq".into());
+        let config = QueueTriggerConfig::from_trigger(&t).unwrap();
+        assert_eq!(config.max_in_flight, 4);
+        assert_eq!(config.dead_letter_queue.as_deref(), Some("dlq"));
+
+        t.config.settings.insert("batch_size".into(), "many".into());
+        assert!(matches!(QueueTriggerConfig::from_trigger(&t), Err(AutomationError::Config(_))));
+    }
+}
@@ -0,0 +1,604 @@
+// Admission control for task resource requests. A task only starts once some node in its
+// execution pool has room for its CPU and memory request; the rest wait in a queue that is
+// drained fairly across workflows as reservations are released.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot;
+use tracing::debug;
+
+use crate::error::{AutomationError, AutomationResult};
+use super::{ResourceRequirements, TaskMetrics};
+
+// Headroom added on top of the peak observed usage when suggesting a request
+const SUGGESTION_HEADROOM: f64 = 1.2;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceQuantity {
+    pub millicpu: u64,
+    pub memory_bytes: u64,
+}
+
+impl ResourceQuantity {
+    pub fn new(millicpu: u64, memory_bytes: u64) -> Self {
+        Self { millicpu, memory_bytes }
+    }
+
+    pub fn from_requirements(resources: &ResourceRequirements) -> AutomationResult<Self> {
+        Ok(Self { millicpu: parse_cpu(&resources.cpu)?, memory_bytes: parse_memory(&resources.memory)? })
+    }
+
+    pub fn fits_in(&self, available: &ResourceQuantity) -> bool {
+        self.millicpu <= available.millicpu && self.memory_bytes <= available.memory_bytes
+    }
+
+    fn saturating_sub(&self, other: &ResourceQuantity) -> ResourceQuantity {
+        ResourceQuantity {
+            millicpu: self.millicpu.saturating_sub(other.millicpu),
+            memory_bytes: self.memory_bytes.saturating_sub(other.memory_bytes),
+        }
+    }
+}
+
+impl std::fmt::Display for ResourceQuantity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}m CPU / {} MiB", self.millicpu, self.memory_bytes / (1024 * 1024))
+    }
+}
+
+// Kubernetes-style quantities: "500m", "2", "0.5"
+pub fn parse_cpu(value: &str) -> AutomationResult<u64> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Ok(0);
+    }
+    let invalid = || AutomationError::Validation(format!("Invalid CPU quantity '{}'", value));
+    match value.strip_suffix('m') {
+        Some(milli) => milli.parse().map_err(|_| invalid()),
+        None => {
+            let cores: f64 = value.parse().map_err(|_| invalid())?;
+            if cores < 0.0 || !cores.is_finite() {
+                return Err(invalid());
+            }
+            Ok((cores * 1000.0).round() as u64)
+        }
+    }
+}
+
+// "512Mi", "1Gi", "1G", or plain bytes
+pub fn parse_memory(value: &str) -> AutomationResult<u64> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Ok(0);
+    }
+    const UNITS: &[(&str, u64)] = &[
+        ("Ki", 1 << 10),
+        ("Mi", 1 << 20),
+        ("Gi", 1 << 30),
+        ("Ti", 1 << 40),
+        ("K", 1_000),
+        ("M", 1_000_000),
+        ("G", 1_000_000_000),
+        ("T", 1_000_000_000_000),
+    ];
+    let (number, multiplier) = UNITS
+        .iter()
+        .find_map(|(suffix, multiplier)| value.strip_suffix(suffix).map(|n| (n, *multiplier)))
+        .unwrap_or((value, 1));
+    let amount: f64 = number
+        .trim()
+        .parse()
+        .map_err(|_| AutomationError::Validation(format!("Invalid memory quantity '{}'", value)))?;
+    if amount < 0.0 || !amount.is_finite() {
+        return Err(AutomationError::Validation(format!("Invalid memory quantity '{}'", value)));
+    }
+    Ok((amount * multiplier as f64).round() as u64)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NodeCapacity {
+    pub node_id: String,
+    // What tasks admitted here may reserve in total
+    pub allocatable: ResourceQuantity,
+}
+
+#[async_trait]
+pub trait CapacitySource: Send + Sync {
+    async fn nodes(&self) -> AutomationResult<Vec<NodeCapacity>>;
+}
+
+// A fixed pool, e.g. the host the local runner is on
+pub struct StaticCapacity {
+    nodes: Vec<NodeCapacity>,
+}
+
+impl StaticCapacity {
+    pub fn new(nodes: Vec<NodeCapacity>) -> Self {
+        Self { nodes }
+    }
+
+    pub fn local(capacity: ResourceQuantity) -> Self {
+        Self::new(vec![NodeCapacity { node_id: "local".into(), allocatable: capacity }])
+    }
+}
+
+#[async_trait]
+impl CapacitySource for StaticCapacity {
+    async fn nodes(&self) -> AutomationResult<Vec<NodeCapacity>> {
+        Ok(self.nodes.clone())
+    }
+}
+
+// Usage of a container the scheduler didn't start, as reported by the runtime's `ContainerStats`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ContainerUsage {
+    // Cores
+    pub cpu_usage: f64,
+    pub memory_usage: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContainerNode {
+    pub node_id: String,
+    pub total: ResourceQuantity,
+    pub containers: Vec<ContainerUsage>,
+}
+
+#[async_trait]
+pub trait ContainerNodeStats: Send + Sync {
+    async fn container_nodes(&self) -> AutomationResult<Vec<ContainerNode>>;
+}
+
+// Container runtime nodes: whatever the node's other containers already use is not allocatable
+pub struct ContainerNodeCapacity {
+    stats: Arc<dyn ContainerNodeStats>,
+}
+
+impl ContainerNodeCapacity {
+    pub fn new(stats: Arc<dyn ContainerNodeStats>) -> Self {
+        Self { stats }
+    }
+}
+
+#[async_trait]
+impl CapacitySource for ContainerNodeCapacity {
+    async fn nodes(&self) -> AutomationResult<Vec<NodeCapacity>> {
+        Ok(self
+            .stats
+            .container_nodes()
+            .await?
+            .into_iter()
+            .map(|node| {
+                let used = ResourceQuantity {
+                    millicpu: node.containers.iter().map(|c| (c.cpu_usage * 1000.0).round() as u64).sum(),
+                    memory_bytes: node.containers.iter().map(|c| c.memory_usage).sum(),
+                };
+                NodeCapacity { node_id: node.node_id, allocatable: node.total.saturating_sub(&used) }
+            })
+            .collect())
+    }
+}
+
+#[async_trait]
+pub trait NodeAllocatable: Send + Sync {
+    // `status.allocatable` per node, as quantity strings keyed by resource name
+    async fn node_allocatable(&self) -> AutomationResult<Vec<(String, HashMap<String, String>)>>;
+}
+
+pub struct KubernetesCapacity {
+    nodes: Arc<dyn NodeAllocatable>,
+}
+
+impl KubernetesCapacity {
+    pub fn new(nodes: Arc<dyn NodeAllocatable>) -> Self {
+        Self { nodes }
+    }
+}
+
+#[async_trait]
+impl CapacitySource for KubernetesCapacity {
+    async fn nodes(&self) -> AutomationResult<Vec<NodeCapacity>> {
+        let mut capacity = Vec::new();
+        for (node_id, allocatable) in self.nodes.node_allocatable().await? {
+            let quantity = |name: &str| allocatable.get(name).map(String::as_str).unwrap_or("");
+            capacity.push(NodeCapacity {
+                node_id,
+                allocatable: ResourceQuantity::new(parse_cpu(quantity("cpu"))?, parse_memory(quantity("memory"))?),
+            });
+        }
+        Ok(capacity)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdmissionRequest {
+    pub workflow_id: String,
+    pub run_id: String,
+    pub task_id: String,
+    pub resources: ResourceQuantity,
+}
+
+struct Node {
+    capacity: NodeCapacity,
+    reserved: ResourceQuantity,
+}
+
+impl Node {
+    fn available(&self) -> ResourceQuantity {
+        self.capacity.allocatable.saturating_sub(&self.reserved)
+    }
+}
+
+struct Waiter {
+    seq: u64,
+    request: AdmissionRequest,
+    tx: oneshot::Sender<Reservation>,
+}
+
+#[derive(Default)]
+struct State {
+    nodes: Vec<Node>,
+    loaded: bool,
+    queue: Vec<Waiter>,
+    next_seq: u64,
+    running: HashMap<String, usize>,
+}
+
+impl State {
+    // Best fit: the node left with the least spare capacity after placing the request
+    fn place(&self, request: &ResourceQuantity) -> Option<usize> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| request.fits_in(&node.available()))
+            .min_by(|(_, a), (_, b)| {
+                let spare = |node: &Node| {
+                    let left = node.available().saturating_sub(request);
+                    let cpu = left.millicpu as f64 / node.capacity.allocatable.millicpu.max(1) as f64;
+                    let memory = left.memory_bytes as f64 / node.capacity.allocatable.memory_bytes.max(1) as f64;
+                    cpu + memory
+                };
+                spare(a).total_cmp(&spare(b))
+            })
+            .map(|(index, _)| index)
+    }
+
+    fn reserve(&mut self, node: usize, request: &AdmissionRequest) {
+        let reserved = &mut self.nodes[node].reserved;
+        reserved.millicpu += request.resources.millicpu;
+        reserved.memory_bytes += request.resources.memory_bytes;
+        *self.running.entry(request.workflow_id.clone()).or_default() += 1;
+    }
+
+    fn release(&mut self, node_id: &str, request: &AdmissionRequest) {
+        if let Some(node) = self.nodes.iter_mut().find(|n| n.capacity.node_id == node_id) {
+            node.reserved = node.reserved.saturating_sub(&request.resources);
+        }
+        if let Some(running) = self.running.get_mut(&request.workflow_id) {
+            *running = running.saturating_sub(1);
+        }
+    }
+
+    fn largest(&self) -> ResourceQuantity {
+        ResourceQuantity {
+            millicpu: self.nodes.iter().map(|n| n.capacity.allocatable.millicpu).max().unwrap_or(0),
+            memory_bytes: self.nodes.iter().map(|n| n.capacity.allocatable.memory_bytes).max().unwrap_or(0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TaskUtilization {
+    pub task_id: String,
+    pub runs: usize,
+    pub requested: ResourceQuantity,
+    pub average_cpu_cores: f64,
+    pub peak_cpu_cores: f64,
+    pub average_memory_bytes: f64,
+    pub peak_memory_bytes: f64,
+    // Average use as a share of the request
+    pub cpu_utilization: f64,
+    pub memory_utilization: f64,
+    pub suggested: ResourceQuantity,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UtilizationReport {
+    pub workflow_id: String,
+    pub tasks: Vec<TaskUtilization>,
+}
+
+pub struct AdmissionController {
+    source: Arc<dyn CapacitySource>,
+    state: Arc<Mutex<State>>,
+    usage: Mutex<HashMap<String, HashMap<String, TaskUtilization>>>,
+}
+
+impl AdmissionController {
+    pub fn new(source: Arc<dyn CapacitySource>) -> Self {
+        Self { source, state: Arc::new(Mutex::new(State::default())), usage: Mutex::new(HashMap::new()) }
+    }
+
+    // Reloads node capacity; existing reservations carry over to nodes that are still present
+    pub async fn refresh(&self) -> AutomationResult<()> {
+        let nodes = self.source.nodes().await?;
+        let mut state = self.state.lock().unwrap();
+        let previous: HashMap<String, ResourceQuantity> =
+            state.nodes.iter().map(|n| (n.capacity.node_id.clone(), n.reserved)).collect();
+        state.nodes = nodes
+            .into_iter()
+            .map(|capacity| Node { reserved: previous.get(&capacity.node_id).copied().unwrap_or_default(), capacity })
+            .collect();
+        state.loaded = true;
+        dispatch(&self.state, &mut state);
+        Ok(())
+    }
+
+    // Waits until the request fits on a node. Requests no single node could ever hold fail at once.
+    pub async fn admit(&self, request: AdmissionRequest) -> AutomationResult<Reservation> {
+        if !self.state.lock().unwrap().loaded {
+            self.refresh().await?;
+        }
+        let rx = {
+            let mut state = self.state.lock().unwrap();
+            let largest = state.largest();
+            if !request.resources.fits_in(&largest) {
+                return Err(AutomationError::Validation(format!(
+                    "Task {} requests {} but the largest node in the pool offers {}",
+                    request.task_id, request.resources, largest
+                )));
+            }
+            let (tx, rx) = oneshot::channel();
+            let seq = state.next_seq;
+            state.next_seq += 1;
+            state.queue.push(Waiter { seq, request, tx });
+            dispatch(&self.state, &mut state);
+            rx
+        };
+        rx.await.map_err(|_| AutomationError::Internal("Admission controller dropped a queued task".into()))
+    }
+
+    pub fn queue_depth(&self) -> usize {
+        self.state.lock().unwrap().queue.len()
+    }
+
+    pub fn available(&self) -> Vec<NodeCapacity> {
+        let state = self.state.lock().unwrap();
+        state
+            .nodes
+            .iter()
+            .map(|n| NodeCapacity { node_id: n.capacity.node_id.clone(), allocatable: n.available() })
+            .collect()
+    }
+
+    // Folds a finished task's measured usage into the workflow's utilization figures
+    pub fn record_usage(&self, workflow_id: &str, task_id: &str, requested: ResourceQuantity, metrics: &TaskMetrics) {
+        if metrics.duration_seconds <= 0 {
+            return;
+        }
+        let duration = metrics.duration_seconds as f64;
+        let cpu = metrics.resource_usage.cpu_seconds / duration;
+        let memory = metrics.resource_usage.memory_mb_seconds / duration * (1024.0 * 1024.0);
+
+        let mut usage = self.usage.lock().unwrap();
+        let entry = usage
+            .entry(workflow_id.to_string())
+            .or_default()
+            .entry(task_id.to_string())
+            .or_insert_with(|| TaskUtilization { task_id: task_id.to_string(), ..Default::default() });
+        let runs = entry.runs as f64;
+        entry.average_cpu_cores = (entry.average_cpu_cores * runs + cpu) / (runs + 1.0);
+        entry.average_memory_bytes = (entry.average_memory_bytes * runs + memory) / (runs + 1.0);
+        entry.peak_cpu_cores = entry.peak_cpu_cores.max(cpu);
+        entry.peak_memory_bytes = entry.peak_memory_bytes.max(memory);
+        entry.runs += 1;
+        entry.requested = requested;
+    }
+
+    pub fn utilization_report(&self, workflow_id: &str) -> UtilizationReport {
+        let usage = self.usage.lock().unwrap();
+        let mut tasks: Vec<TaskUtilization> = usage
+            .get(workflow_id)
+            .map(|tasks| tasks.values().cloned().collect())
+            .unwrap_or_default();
+        for task in &mut tasks {
+            let ratio = |used: f64, requested: u64| if requested == 0 { 0.0 } else { used / requested as f64 };
+            task.cpu_utilization = ratio(task.average_cpu_cores * 1000.0, task.requested.millicpu);
+            task.memory_utilization = ratio(task.average_memory_bytes, task.requested.memory_bytes);
+            task.suggested = ResourceQuantity {
+                millicpu: (task.peak_cpu_cores * 1000.0 * SUGGESTION_HEADROOM).ceil() as u64,
+                memory_bytes: (task.peak_memory_bytes * SUGGESTION_HEADROOM).ceil() as u64,
+            };
+        }
+        tasks.sort_by(|a, b| a.task_id.cmp(&b.task_id));
+        UtilizationReport { workflow_id: workflow_id.to_string(), tasks }
+    }
+}
+
+// Hands out capacity to queued requests: workflows with the fewest running tasks go first, then
+// arrival order. The request at the front waits for room rather than being passed by smaller
+// ones, so large requests can't be starved.
+fn dispatch(shared: &Arc<Mutex<State>>, state: &mut State) {
+    loop {
+        let Some(index) = (0..state.queue.len()).min_by_key(|&i| {
+            let waiter = &state.queue[i];
+            (state.running.get(&waiter.request.workflow_id).copied().unwrap_or(0), waiter.seq)
+        }) else {
+            return;
+        };
+        let Some(node) = state.place(&state.queue[index].request.resources) else {
+            return;
+        };
+        let waiter = state.queue.remove(index);
+        state.reserve(node, &waiter.request);
+        let node_id = state.nodes[node].capacity.node_id.clone();
+        debug!("Admitted task {} of {} on {}", waiter.request.task_id, waiter.request.workflow_id, node_id);
+        let reservation = Reservation { state: Some(shared.clone()), node_id, request: waiter.request };
+        if let Err(mut reservation) = waiter.tx.send(reservation) {
+            // The caller stopped waiting; give the capacity straight back
+            reservation.state = None;
+            state.release(&reservation.node_id, &reservation.request);
+        }
+    }
+}
+
+// Capacity held for one running task, returned to the pool on drop
+pub struct Reservation {
+    state: Option<Arc<Mutex<State>>>,
+    node_id: String,
+    request: AdmissionRequest,
+}
+
+impl Reservation {
+    pub fn node_id(&self) -> &str {
+        &self.node_id
+    }
+
+    pub fn request(&self) -> &AdmissionRequest {
+        &self.request
+    }
+}
+
+impl std::fmt::Debug for Reservation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Reservation").field("node_id", &self.node_id).field("request", &self.request).finish()
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        if let Some(shared) = self.state.take() {
+            let mut state = shared.lock().unwrap();
+            state.release(&self.node_id, &self.request);
+            dispatch(&shared, &mut state);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workflow::ResourceUsage;
+    use std::time::Duration;
+
+    const GIB: u64 = 1 << 30;
+
+    fn request(workflow_id: &str, task_id: &str, millicpu: u64, memory_bytes: u64) -> AdmissionRequest {
+        AdmissionRequest {
+            workflow_id: workflow_id.into(),
+            run_id: format!("{}-run", workflow_id),
+            task_id: task_id.into(),
+            resources: ResourceQuantity::new(millicpu, memory_bytes),
+        }
+    }
+
+    fn pool(nodes: &[(&str, u64, u64)]) -> Arc<AdmissionController> {
+        let nodes = nodes
+            .iter()
+            .map(|(id, cpu, memory)| NodeCapacity { node_id: id.to_string(), allocatable: ResourceQuantity::new(*cpu, *memory) })
+            .collect();
+        Arc::new(AdmissionController::new(Arc::new(StaticCapacity::new(nodes))))
+    }
+
+    #[tokio::test]
+    async fn test_fit_and_impossible_request_rejection() {
+        let requirements = ResourceRequirements { cpu: "1.5".into(), memory: "512Mi".into(), storage: None, gpu: None };
+        assert_eq!(ResourceQuantity::from_requirements(&requirements).unwrap(), ResourceQuantity::new(1500, 512 << 20));
+        assert_eq!(parse_cpu("250m").unwrap(), 250);
+        assert_eq!(parse_memory("2G").unwrap(), 2_000_000_000);
+        assert!(parse_memory("lots").is_err());
+
+        // 8 CPUs across the pool, but no single node has 6
+        let controller = pool(&[("small", 2000, 4 * GIB), ("medium", 4000, 8 * GIB)]);
+        let err = controller.admit(request("etl", "transform", 6000, GIB)).await.unwrap_err().to_string();
+        assert!(err.contains("Task transform requests 6000m CPU / 1024 MiB"), "{}", err);
+        assert!(err.contains("largest node in the pool offers 4000m CPU / 8192 MiB"), "{}", err);
+        assert!(controller.admit(request("etl", "load", 1000, 16 * GIB)).await.is_err());
+
+        // Best fit puts the 2-CPU task on the small node and keeps the medium one whole
+        let first = controller.admit(request("etl", "extract", 2000, 2 * GIB)).await.unwrap();
+        assert_eq!(first.node_id(), "small");
+        let second = controller.admit(request("etl", "transform", 3000, 6 * GIB)).await.unwrap();
+        assert_eq!(second.node_id(), "medium");
+        let available: HashMap<String, ResourceQuantity> =
+            controller.available().into_iter().map(|n| (n.node_id, n.allocatable)).collect();
+        assert_eq!(available["small"], ResourceQuantity::new(0, 2 * GIB));
+        assert_eq!(available["medium"], ResourceQuantity::new(1000, 2 * GIB));
+
+        drop(first);
+        assert_eq!(controller.available().iter().find(|n| n.node_id == "small").unwrap().allocatable, ResourceQuantity::new(2000, 4 * GIB));
+    }
+
+    #[tokio::test]
+    async fn test_queue_drains_fairly_across_workflows() {
+        let controller = pool(&[("node-1", 4000, 16 * GIB)]);
+        let mut held = Vec::new();
+        for i in 0..4 {
+            held.push(controller.admit(request("nightly", &format!("shard-{}", i), 1000, GIB)).await.unwrap());
+        }
+
+        let admitted = Arc::new(Mutex::new(Vec::new()));
+        let mut waiting = Vec::new();
+        for (workflow, task, cpu) in [("nightly", "shard-4", 1000), ("nightly", "shard-5", 1000), ("adhoc", "big", 2000), ("adhoc", "small", 1000)] {
+            let (controller, admitted) = (controller.clone(), admitted.clone());
+            waiting.push(tokio::spawn(async move {
+                let reservation = controller.admit(request(workflow, task, cpu, GIB)).await.unwrap();
+                admitted.lock().unwrap().push(task);
+                reservation
+            }));
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        assert_eq!(controller.queue_depth(), 4);
+
+        // One CPU frees up: "adhoc" has nothing running so "big" is at the front, and it keeps
+        // its place until a second CPU is free even though "small" would fit
+        held.pop();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        assert!(admitted.lock().unwrap().is_empty());
+        held.pop();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        assert_eq!(*admitted.lock().unwrap(), vec!["big"]);
+
+        // Each release leaves both workflows with one task running, so arrival order breaks the tie
+        held.pop();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        assert_eq!(*admitted.lock().unwrap(), vec!["big", "shard-4"]);
+        held.pop();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        assert_eq!(*admitted.lock().unwrap(), vec!["big", "shard-4", "shard-5"]);
+        assert_eq!(controller.queue_depth(), 1);
+
+        for handle in waiting.drain(..3) {
+            drop(handle.await.unwrap());
+        }
+        waiting.pop().unwrap().await.unwrap();
+        assert_eq!(controller.queue_depth(), 0);
+        assert_eq!(*admitted.lock().unwrap(), vec!["big", "shard-4", "shard-5", "small"]);
+    }
+
+    #[test]
+    fn test_utilization_report_compares_usage_to_requests() {
+        let controller = pool(&[("node-1", 4000, 16 * GIB)]);
+        let requested = ResourceQuantity::new(2000, 4 * GIB);
+        for (cpu_seconds, memory_mb_seconds) in [(50.0, 51_200.0), (150.0, 102_400.0)] {
+            let metrics = TaskMetrics {
+                duration_seconds: 100,
+                retry_count: 0,
+                resource_usage: ResourceUsage { cpu_seconds, memory_mb_seconds, io_bytes: 0 },
+            };
+            controller.record_usage("nightly", "shard", requested, &metrics);
+        }
+
+        let report = controller.utilization_report("nightly");
+        let task = &report.tasks[0];
+        assert_eq!(task.runs, 2);
+        assert!((task.average_cpu_cores - 1.0).abs() < 1e-9);
+        assert!((task.cpu_utilization - 0.5).abs() < 1e-9);
+        assert!((task.memory_utilization - 768.0 / 4096.0).abs() < 1e-9);
+        assert_eq!(task.suggested, ResourceQuantity::new(1800, (1024.0 * 1.2 * (1 << 20) as f64).ceil() as u64));
+        assert!(controller.utilization_report("other").tasks.is_empty());
+    }
+}
@@ -0,0 +1,467 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::AutomationResult;
+use crate::metrics::status_label;
+use super::{DependencyType, RunStatus, Task, TaskDependency, TaskRun, TaskType, Workflow, WorkflowManager, WorkflowRun};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GraphFormat {
+    Dot,
+    Mermaid,
+}
+
+// Renders workflow DAGs for the UI. Tasks are emitted sorted by id and edges by (from, to), so the
+// same workflow always produces the same text.
+pub struct GraphExporter {
+    workflows: Arc<dyn WorkflowManager>,
+}
+
+impl GraphExporter {
+    pub fn new(workflows: Arc<dyn WorkflowManager>) -> Self {
+        Self { workflows }
+    }
+
+    pub async fn export_graph(&self, workflow_id: &str, format: GraphFormat) -> AutomationResult<String> {
+        let workflow = self.workflows.get_workflow(workflow_id).await?;
+        Ok(render_workflow(&workflow, format))
+    }
+
+    // Overlays the run on the workflow's current definition; tasks the run never reached are left
+    // uncoloured
+    pub async fn export_run_graph(&self, run_id: &str, format: GraphFormat) -> AutomationResult<String> {
+        let run = self.workflows.get_workflow_run(run_id).await?;
+        let workflow = self.workflows.get_workflow(&run.workflow_id).await?;
+        Ok(render_run(&workflow, &run, format))
+    }
+}
+
+pub fn render_workflow(workflow: &Workflow, format: GraphFormat) -> String {
+    Graph::new(workflow, None).render(format)
+}
+
+pub fn render_run(workflow: &Workflow, run: &WorkflowRun, format: GraphFormat) -> String {
+    Graph::new(workflow, Some(run)).render(format)
+}
+
+struct Node<'a> {
+    task: &'a Task,
+    mermaid_id: String,
+    run: Option<&'a TaskRun>,
+}
+
+struct Edge<'a> {
+    from: &'a str,
+    to: &'a str,
+    label: Option<String>,
+    failure: bool,
+}
+
+struct Graph<'a> {
+    title: String,
+    nodes: BTreeMap<&'a str, Node<'a>>,
+    edges: Vec<Edge<'a>>,
+    overlay: bool,
+}
+
+impl<'a> Graph<'a> {
+    fn new(workflow: &'a Workflow, run: Option<&'a WorkflowRun>) -> Self {
+        // A retried task has several task runs; the latest one is what the overlay shows
+        let mut latest: HashMap<&str, &TaskRun> = HashMap::new();
+        for task_run in run.map(|run| run.task_runs.as_slice()).unwrap_or_default() {
+            let entry = latest.entry(task_run.task_id.as_str()).or_insert(task_run);
+            if task_run.start_time >= entry.start_time {
+                *entry = task_run;
+            }
+        }
+
+        let mut sorted: Vec<&Task> = workflow.tasks.iter().collect();
+        sorted.sort_by(|a, b| a.id.cmp(&b.id));
+        let mut used = HashMap::new();
+        let mut nodes = BTreeMap::new();
+        for task in sorted {
+            let base = mermaid_id(&task.id);
+            let count = used.entry(base.clone()).or_insert(0usize);
+            *count += 1;
+            let mermaid_id = if *count == 1 { base } else { format!("{}_{}", base, count) };
+            nodes.insert(task.id.as_str(), Node { task, mermaid_id, run: latest.get(task.id.as_str()).copied() });
+        }
+
+        let mut edges: Vec<Edge> = workflow
+            .tasks
+            .iter()
+            .flat_map(|task| task.dependencies.iter().map(move |dependency| (task, dependency)))
+            .filter(|(_, dependency)| nodes.contains_key(dependency.task_id.as_str()))
+            .map(|(task, dependency)| Edge {
+                from: dependency.task_id.as_str(),
+                to: task.id.as_str(),
+                label: edge_label(dependency),
+                failure: matches!(dependency.type_, DependencyType::Failure),
+            })
+            .collect();
+        edges.sort_by(|a, b| (a.from, a.to, &a.label).cmp(&(b.from, b.to, &b.label)));
+
+        let title = match run {
+            Some(run) => format!("{} (run {}: {})", workflow.name, run.id, status_label(&run.status)),
+            None => workflow.name.clone(),
+        };
+        Self { title, nodes, edges, overlay: run.is_some() }
+    }
+
+    fn render(&self, format: GraphFormat) -> String {
+        match format {
+            GraphFormat::Dot => self.dot(),
+            GraphFormat::Mermaid => self.mermaid(),
+        }
+    }
+
+    fn label(&self, node: &Node) -> String {
+        let mut label = format!("{}\n{}", node.task.name, node.task.task_type.kind());
+        if self.overlay {
+            match node.run {
+                Some(run) => {
+                    label.push_str(&format!("\n{}", status_label(&run.status)));
+                    if let Some(end) = run.end_time {
+                        label.push_str(&format!(" in {}", format_duration((end - run.start_time).num_seconds())));
+                    }
+                }
+                None => label.push_str("\nnot run"),
+            }
+        }
+        label
+    }
+
+    fn dot(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "digraph {} {{", dot_quote(&self.title));
+        let _ = writeln!(out, "  rankdir=LR;");
+        let _ = writeln!(out, "  label={};", dot_quote(&self.title));
+        let _ = writeln!(out, "  node [fontname=\"Helvetica\"];");
+        for (id, node) in &self.nodes {
+            let kind = node.task.task_type.kind();
+            let mut attributes = vec![
+                format!("label={}", dot_quote(&self.label(node))),
+                format!("shape={}", dot_shape(&node.task.task_type)),
+                format!("class=\"{}\"", kind),
+            ];
+            if let Some(run) = node.run {
+                attributes.push("style=filled".into());
+                attributes.push(format!("fillcolor=\"{}\"", status_color(&run.status)));
+            } else if self.overlay {
+                attributes.push("style=dashed".into());
+            }
+            let _ = writeln!(out, "  {} [{}];", dot_quote(id), attributes.join(", "));
+        }
+        for edge in &self.edges {
+            let mut attributes = Vec::new();
+            if let Some(label) = &edge.label {
+                attributes.push(format!("label={}", dot_quote(label)));
+            }
+            if edge.failure {
+                attributes.push("style=dashed".into());
+            }
+            let suffix = if attributes.is_empty() { String::new() } else { format!(" [{}]", attributes.join(", ")) };
+            let _ = writeln!(out, "  {} -> {}{};", dot_quote(edge.from), dot_quote(edge.to), suffix);
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    fn mermaid(&self) -> String {
+        let mut out = String::new();
+        // The title sits in YAML front matter, where a JSON string is a valid quoted scalar
+        let title = serde_json::to_string(&self.title).unwrap_or_default();
+        let _ = writeln!(out, "---\ntitle: {}\n---", title);
+        let _ = writeln!(out, "flowchart LR");
+        for node in self.nodes.values() {
+            let (open, close) = mermaid_shape(&node.task.task_type);
+            let label = mermaid_escape(&self.label(node));
+            let _ = writeln!(out, "  {}{}\"{}\"{}", node.mermaid_id, open, label, close);
+        }
+        for edge in &self.edges {
+            let arrow = if edge.failure { "-.->" } else { "-->" };
+            let (from, to) = (&self.nodes[edge.from].mermaid_id, &self.nodes[edge.to].mermaid_id);
+            match &edge.label {
+                Some(label) => {
+                    let _ = writeln!(out, "  {} {}|\"{}\"| {}", from, arrow, mermaid_escape(label), to);
+                }
+                None => {
+                    let _ = writeln!(out, "  {} {} {}", from, arrow, to);
+                }
+            }
+        }
+
+        let mut classes: BTreeMap<String, Vec<&str>> = BTreeMap::new();
+        for node in self.nodes.values() {
+            classes.entry(node.task.task_type.kind().to_string()).or_default().push(&node.mermaid_id);
+            if self.overlay {
+                let status = node.run.map(|run| status_label(&run.status)).unwrap_or("not_run");
+                classes.entry(format!("status_{}", status)).or_default().push(&node.mermaid_id);
+            }
+        }
+        if self.overlay {
+            for status in [
+                RunStatus::Pending,
+                RunStatus::Running,
+                RunStatus::Succeeded,
+                RunStatus::Failed,
+                RunStatus::Cancelled,
+                RunStatus::TimedOut,
+            ] {
+                let _ = writeln!(out, "  classDef status_{} fill:{}", status_label(&status), status_color(&status));
+            }
+            let _ = writeln!(out, "  classDef status_not_run stroke-dasharray:4");
+        }
+        for (class, ids) in classes {
+            let _ = writeln!(out, "  class {} {}", ids.join(","), class);
+        }
+        out
+    }
+}
+
+fn edge_label(dependency: &TaskDependency) -> Option<String> {
+    let kind = match &dependency.type_ {
+        DependencyType::Success => None,
+        DependencyType::Failure => Some("on failure".to_string()),
+        DependencyType::Completed => Some("on completion".to_string()),
+        DependencyType::Data { key } => Some(format!("data: {}", key)),
+    };
+    let condition = dependency.condition.as_ref().map(|condition| format!("if {}", condition));
+    match (kind, condition) {
+        (Some(kind), Some(condition)) => Some(format!("{}, {}", kind, condition)),
+        (kind, condition) => kind.or(condition),
+    }
+}
+
+fn dot_shape(task_type: &TaskType) -> &'static str {
+    match task_type {
+        TaskType::Script { .. } => "box",
+        TaskType::Container { .. } => "box3d",
+        TaskType::Function { .. } => "component",
+        TaskType::HTTP { .. } => "cds",
+        TaskType::AWS { .. } | TaskType::GCP { .. } | TaskType::Azure { .. } => "tab",
+        TaskType::Kubernetes { .. } => "hexagon",
+        TaskType::Database { .. } => "cylinder",
+        TaskType::Queue { .. } => "parallelogram",
+        TaskType::Notification { .. } => "note",
+    }
+}
+
+fn mermaid_shape(task_type: &TaskType) -> (&'static str, &'static str) {
+    match task_type {
+        TaskType::Script { .. } => ("[", "]"),
+        TaskType::Container { .. } => ("[[", "]]"),
+        TaskType::Function { .. } => ("(", ")"),
+        TaskType::HTTP { .. } => (">", "]"),
+        TaskType::AWS { .. } | TaskType::GCP { .. } | TaskType::Azure { .. } => ("([", "])"),
+        TaskType::Kubernetes { .. } => ("{{", "}}"),
+        TaskType::Database { .. } => ("[(", ")]"),
+        TaskType::Queue { .. } => ("[/", "/]"),
+        TaskType::Notification { .. } => ("((", "))"),
+    }
+}
+
+fn status_color(status: &RunStatus) -> &'static str {
+    match status {
+        RunStatus::Pending => "#e0e0e0",
+        RunStatus::Running => "#90caf9",
+        RunStatus::Succeeded => "#a5d6a7",
+        RunStatus::Failed => "#ef9a9a",
+        RunStatus::Cancelled => "#fff59d",
+        RunStatus::TimedOut => "#ffcc80",
+    }
+}
+
+fn format_duration(seconds: i64) -> String {
+    let seconds = seconds.max(0);
+    match seconds {
+        0..=59 => format!("{}s", seconds),
+        60..=3599 => format!("{}m {}s", seconds / 60, seconds % 60),
+        _ => format!("{}h {}m", seconds / 3600, seconds % 3600 / 60),
+    }
+}
+
+fn dot_quote(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => {}
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// Mermaid labels go inside double quotes, where only entity codes are safe for markup characters
+fn mermaid_escape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("#quot;"),
+            '#' => out.push_str("#35;"),
+            '<' => out.push_str("#lt;"),
+            '>' => out.push_str("#gt;"),
+            '|' => out.push_str("#124;"),
+            '\n' => out.push_str("<br/>"),
+            '\r' => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+// Mermaid node ids must be plain identifiers; `Graph::new` appends a suffix if two task ids collapse
+// to the same one
+fn mermaid_id(task_id: &str) -> String {
+    let id: String = task_id.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect();
+    format!("t_{}", id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workflow::{
+        ResourceRequirements, ResourceUsage, RunMetrics, RunTrigger, TaskConfig, TaskMetrics, TriggerType,
+        WorkflowStatus,
+    };
+    use chrono::{DateTime, Duration, Utc};
+
+    fn task(id: &str, name: &str, task_type: TaskType, dependencies: Vec<TaskDependency>) -> Task {
+        Task {
+            id: id.into(),
+            name: name.into(),
+            task_type,
+            config: TaskConfig {
+                inputs: HashMap::new(),
+                environment: HashMap::new(),
+                resources: ResourceRequirements { cpu: "500m".into(), memory: "256Mi".into(), storage: None, gpu: None },
+                secrets: Vec::new(),
+                artifacts: Vec::new(),
+            },
+            dependencies,
+            retry_policy: None,
+            timeout: None,
+            on_failure: None,
+            conditions: Vec::new(),
+        }
+    }
+
+    fn after(task_id: &str, type_: DependencyType, condition: Option<&str>) -> TaskDependency {
+        TaskDependency { task_id: task_id.into(), type_, condition: condition.map(str::to_string) }
+    }
+
+    fn workflow() -> Workflow {
+        let now = DateTime::parse_from_rfc3339("2026-01-01T00:00:00Z").unwrap().with_timezone(&Utc);
+        Workflow {
+            id: "wf-release".into(),
+            name: "Release \"v2\"".into(),
+            description: String::new(),
+            version: "3".into(),
+            // Declared out of order to check the output is sorted
+            tasks: vec![
+                task(
+                    "notify",
+                    "Tell #ops",
+                    TaskType::Notification { channel: "slack".into() },
+                    vec![after("deploy", DependencyType::Completed, None)],
+                ),
+                task(
+                    "deploy",
+                    "Deploy\nto prod",
+                    TaskType::Kubernetes { resource: "deployment".into(), action: "apply".into() },
+                    vec![after("build", DependencyType::Success, Some("outputs.tests == \"passed\""))],
+                ),
+                task("build", "Build", TaskType::Container { image: "rust:1.78".into() }, Vec::new()),
+                task(
+                    "rollback",
+                    "Roll back",
+                    TaskType::Script { runtime: "bash".into() },
+                    vec![after("deploy", DependencyType::Failure, None)],
+                ),
+                task(
+                    "migrate",
+                    "Migrate",
+                    TaskType::Database { operation: "migrate".into() },
+                    vec![after("build", DependencyType::Data { key: "schema".into() }, None)],
+                ),
+            ],
+            triggers: Vec::new(),
+            status: WorkflowStatus::Active,
+            schedule: None,
+            variables: HashMap::new(),
+            timeout: None,
+            created_at: now,
+            updated_at: now,
+            metadata: HashMap::new(),
+        }
+    }
+
+    fn task_run(task_id: &str, status: RunStatus, start: DateTime<Utc>, seconds: Option<i64>) -> TaskRun {
+        TaskRun {
+            id: format!("{}-run", task_id),
+            task_id: task_id.into(),
+            status,
+            start_time: start,
+            end_time: seconds.map(|seconds| start + Duration::seconds(seconds)),
+            inputs: HashMap::new(),
+            outputs: HashMap::new(),
+            error: None,
+            logs_uri: None,
+            metrics: TaskMetrics {
+                duration_seconds: seconds.unwrap_or(0),
+                retry_count: 0,
+                resource_usage: ResourceUsage { cpu_seconds: 0.0, memory_mb_seconds: 0.0, io_bytes: 0 },
+            },
+        }
+    }
+
+    #[test]
+    fn test_workflow_graph_matches_golden_files() {
+        let workflow = workflow();
+        assert_eq!(render_workflow(&workflow, GraphFormat::Dot), include_str!("fixtures/release.dot"));
+        assert_eq!(render_workflow(&workflow, GraphFormat::Mermaid), include_str!("fixtures/release.mmd"));
+    }
+
+    #[test]
+    fn test_partially_failed_run_overlay_matches_golden_files() {
+        let workflow = workflow();
+        let start = workflow.created_at;
+        let run = WorkflowRun {
+            id: "run-7".into(),
+            workflow_id: workflow.id.clone(),
+            version: workflow.version.clone(),
+            status: RunStatus::Failed,
+            trigger: RunTrigger { type_: TriggerType::Webhook, source: "ci".into(), event: None },
+            task_runs: vec![
+                task_run("build", RunStatus::Succeeded, start, Some(312)),
+                task_run("migrate", RunStatus::Succeeded, start + Duration::seconds(320), Some(45)),
+                // The first deploy attempt timed out; the retry failed outright
+                task_run("deploy", RunStatus::TimedOut, start + Duration::seconds(320), Some(600)),
+                task_run("deploy", RunStatus::Failed, start + Duration::seconds(930), Some(4000)),
+                task_run("rollback", RunStatus::Running, start + Duration::seconds(4935), None),
+            ],
+            variables: HashMap::new(),
+            start_time: start,
+            end_time: None,
+            metrics: RunMetrics {
+                total_duration_seconds: 0,
+                task_count: 5,
+                failed_tasks: 1,
+                retried_tasks: 1,
+                resource_usage: ResourceUsage { cpu_seconds: 0.0, memory_mb_seconds: 0.0, io_bytes: 0 },
+            },
+        };
+
+        assert_eq!(render_run(&workflow, &run, GraphFormat::Dot), include_str!("fixtures/release-run.dot"));
+        assert_eq!(render_run(&workflow, &run, GraphFormat::Mermaid), include_str!("fixtures/release-run.mmd"));
+    }
+}
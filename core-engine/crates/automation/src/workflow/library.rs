@@ -0,0 +1,734 @@
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::artifact::ObjectStore;
+use crate::error::{AutomationError, AutomationResult};
+use crate::trigger::value_to_string;
+use super::{Task, Value, VariableType, Workflow};
+
+const TEMPLATE_PREFIX: &str = "workflow-templates/";
+
+pub const TEMPLATE_ID_KEY: &str = "template_id";
+pub const TEMPLATE_VERSION_KEY: &str = "template_version";
+pub const PROJECT_ID_KEY: &str = "project_id";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParameterRule {
+    Min(f64),
+    Max(f64),
+    // Characters for strings, elements for arrays
+    MinLength(usize),
+    MaxLength(usize),
+    OneOf(Vec<String>),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateParameter {
+    pub name: String,
+    pub type_: VariableType,
+    pub description: Option<String>,
+    pub default: Option<Value>,
+    pub required: bool,
+    #[serde(default)]
+    pub rules: Vec<ParameterRule>,
+}
+
+impl TemplateParameter {
+    pub fn new(name: impl Into<String>, type_: VariableType) -> Self {
+        Self { name: name.into(), type_, description: None, default: None, required: true, rules: Vec::new() }
+    }
+
+    pub fn with_default(mut self, default: Value) -> Self {
+        self.default = Some(default);
+        self.required = false;
+        self
+    }
+
+    pub fn with_rule(mut self, rule: ParameterRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    // Integers are accepted for float parameters and widened
+    fn check(&self, value: &Value) -> Result<Value, String> {
+        let value = match (&self.type_, value) {
+            (VariableType::String | VariableType::Secret, Value::String(_))
+            | (VariableType::Integer, Value::Integer(_))
+            | (VariableType::Float, Value::Float(_))
+            | (VariableType::Boolean, Value::Boolean(_))
+            | (VariableType::Array, Value::Array(_))
+            | (VariableType::Object, Value::Object(_)) => value.clone(),
+            (VariableType::Float, Value::Integer(i)) => Value::Float(*i as f64),
+            _ => return Err(format!("expected {:?}", self.type_)),
+        };
+        if value_to_string(&value).contains("{{") {
+            return Err("values may not contain placeholders".into());
+        }
+        for rule in &self.rules {
+            let number = match &value {
+                Value::Integer(i) => Some(*i as f64),
+                Value::Float(f) => Some(*f),
+                _ => None,
+            };
+            let length = match &value {
+                Value::String(s) => Some(s.chars().count()),
+                Value::Array(items) => Some(items.len()),
+                _ => None,
+            };
+            let broken = match rule {
+                ParameterRule::Min(min) => number.is_some_and(|n| n < *min),
+                ParameterRule::Max(max) => number.is_some_and(|n| n > *max),
+                ParameterRule::MinLength(min) => length.is_some_and(|l| l < *min),
+                ParameterRule::MaxLength(max) => length.is_some_and(|l| l > *max),
+                ParameterRule::OneOf(allowed) => !allowed.contains(&value_to_string(&value)),
+            };
+            if broken {
+                return Err(format!("violates {:?}", rule));
+            }
+        }
+        Ok(value)
+    }
+}
+
+// A workflow whose task configs contain `{{ params.<name> }}` placeholders. Other placeholder
+// roots (variables, outputs, ...) are left for the notification renderer at run time.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkflowTemplate {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    // Assigned by the library on publish, starting at 1
+    pub version: u32,
+    pub parameters: Vec<TemplateParameter>,
+    pub workflow: Workflow,
+    pub published_at: DateTime<Utc>,
+}
+
+impl WorkflowTemplate {
+    pub fn new(id: impl Into<String>, name: impl Into<String>, workflow: Workflow) -> Self {
+        Self {
+            id: id.into(),
+            name: name.into(),
+            description: String::new(),
+            version: 0,
+            parameters: Vec::new(),
+            workflow,
+            published_at: Utc::now(),
+        }
+    }
+
+    pub fn with_parameter(mut self, parameter: TemplateParameter) -> Self {
+        self.parameters.push(parameter);
+        self
+    }
+
+    // Applies defaults and checks each value; parameters the template doesn't declare are rejected
+    fn resolve(&self, params: &HashMap<String, Value>) -> AutomationResult<HashMap<String, Value>> {
+        let mut problems = Vec::new();
+        for name in params.keys() {
+            if !self.parameters.iter().any(|p| &p.name == name) {
+                problems.push(format!("unknown parameter '{}'", name));
+            }
+        }
+        let mut resolved = HashMap::new();
+        for parameter in &self.parameters {
+            match params.get(&parameter.name).or(parameter.default.as_ref()) {
+                Some(value) => match parameter.check(value) {
+                    Ok(value) => {
+                        resolved.insert(parameter.name.clone(), value);
+                    }
+                    Err(e) => problems.push(format!("parameter '{}' {}", parameter.name, e)),
+                },
+                None if parameter.required => problems.push(format!("missing required parameter '{}'", parameter.name)),
+                None => {}
+            }
+        }
+        if problems.is_empty() {
+            Ok(resolved)
+        } else {
+            problems.sort();
+            Err(AutomationError::Validation(format!("Invalid template parameters: {}", problems.join("; "))))
+        }
+    }
+
+    // Optional parameters left unset render as empty strings
+    fn render(&self, params: &HashMap<String, Value>) -> AutomationResult<Workflow> {
+        let mut params = params.clone();
+        for parameter in &self.parameters {
+            params.entry(parameter.name.clone()).or_insert_with(|| Value::String(String::new()));
+        }
+        let params = &params;
+        let mut unknown = BTreeSet::new();
+        let mut workflow = self.workflow.clone();
+        for task in &mut workflow.tasks {
+            for input in task.config.inputs.values_mut() {
+                substitute_value(input, params, &mut unknown);
+            }
+        }
+        let mut json = serde_json::to_value(&workflow).map_err(|e| AutomationError::Internal(e.to_string()))?;
+        substitute_json(&mut json, params, &mut unknown);
+        if !unknown.is_empty() {
+            return Err(AutomationError::Validation(format!(
+                "Template {} references undeclared parameters: {}",
+                self.id,
+                unknown.into_iter().collect::<Vec<_>>().join(", ")
+            )));
+        }
+        serde_json::from_value(json).map_err(|e| AutomationError::Internal(e.to_string()))
+    }
+}
+
+// `{{ params.name }}` with nothing around it; such inputs take the parameter's typed value
+fn whole_placeholder(s: &str) -> Option<&str> {
+    let inner = s.trim().strip_prefix("{{")?.strip_suffix("}}")?;
+    if inner.contains("{{") || inner.contains("}}") {
+        return None;
+    }
+    inner.trim().strip_prefix("params.")
+}
+
+fn interpolate(s: &str, params: &HashMap<String, Value>, unknown: &mut BTreeSet<String>) -> String {
+    let mut out = String::new();
+    let mut rest = s;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}").map(|end| start + end) else { break };
+        match rest[start + 2..end].trim().strip_prefix("params.") {
+            Some(name) => {
+                out.push_str(&rest[..start]);
+                match params.get(name) {
+                    Some(value) => out.push_str(&value_to_string(value)),
+                    None => {
+                        unknown.insert(name.to_string());
+                    }
+                }
+            }
+            None => out.push_str(&rest[..end + 2]),
+        }
+        rest = &rest[end + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+fn substitute_value(value: &mut Value, params: &HashMap<String, Value>, unknown: &mut BTreeSet<String>) {
+    match value {
+        Value::String(s) => match whole_placeholder(s) {
+            Some(name) => match params.get(name) {
+                Some(param) => *value = param.clone(),
+                None => {
+                    unknown.insert(name.to_string());
+                }
+            },
+            None => *s = interpolate(s, params, unknown),
+        },
+        Value::Array(items) => items.iter_mut().for_each(|item| substitute_value(item, params, unknown)),
+        Value::Object(fields) => fields.values_mut().for_each(|field| substitute_value(field, params, unknown)),
+        Value::Integer(_) | Value::Float(_) | Value::Boolean(_) | Value::Reference(_) => {}
+    }
+}
+
+fn substitute_json(json: &mut serde_json::Value, params: &HashMap<String, Value>, unknown: &mut BTreeSet<String>) {
+    match json {
+        serde_json::Value::String(s) => *s = interpolate(s, params, unknown),
+        serde_json::Value::Array(items) => items.iter_mut().for_each(|item| substitute_json(item, params, unknown)),
+        serde_json::Value::Object(fields) => fields.values_mut().for_each(|field| substitute_json(field, params, unknown)),
+        _ => {}
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateLineage {
+    pub template_id: String,
+    pub template_version: u32,
+    // As resolved, defaults included, so the workflow can be re-rendered from a newer version
+    pub parameters: HashMap<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateInstance {
+    pub workflow: Workflow,
+    pub project_id: String,
+    pub lineage: TemplateLineage,
+    pub instantiated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "change")]
+pub enum TaskChange {
+    Added { task_id: String },
+    Removed { task_id: String },
+    Changed { task_id: String, fields: Vec<String> },
+}
+
+// What re-instantiating one workflow from the latest template version would change
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstanceDiff {
+    pub workflow_id: String,
+    pub project_id: String,
+    pub from_version: u32,
+    pub to_version: u32,
+    pub workflow_fields: Vec<String>,
+    pub tasks: Vec<TaskChange>,
+    // New required parameters the instance never supplied; it can't be re-instantiated as-is
+    pub missing_parameters: Vec<String>,
+}
+
+// Fields that legitimately differ between two renders of the same template
+const IGNORED_WORKFLOW_FIELDS: &[&str] = &["id", "version", "created_at", "updated_at", "metadata", "tasks"];
+
+fn changed_fields(old: &serde_json::Value, new: &serde_json::Value, ignored: &[&str]) -> Vec<String> {
+    let (Some(old), Some(new)) = (old.as_object(), new.as_object()) else {
+        return Vec::new();
+    };
+    let keys: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    keys.into_iter()
+        .filter(|key| !ignored.contains(&key.as_str()) && old.get(*key) != new.get(*key))
+        .cloned()
+        .collect()
+}
+
+fn diff_tasks(old: &[Task], new: &[Task]) -> AutomationResult<Vec<TaskChange>> {
+    let json = |task: &Task| serde_json::to_value(task).map_err(|e| AutomationError::Internal(e.to_string()));
+    let mut changes = Vec::new();
+    for task in new {
+        match old.iter().find(|t| t.id == task.id) {
+            None => changes.push(TaskChange::Added { task_id: task.id.clone() }),
+            Some(previous) => {
+                let fields = changed_fields(&json(previous)?, &json(task)?, &[]);
+                if !fields.is_empty() {
+                    changes.push(TaskChange::Changed { task_id: task.id.clone(), fields });
+                }
+            }
+        }
+    }
+    for task in old {
+        if !new.iter().any(|t| t.id == task.id) {
+            changes.push(TaskChange::Removed { task_id: task.id.clone() });
+        }
+    }
+    Ok(changes)
+}
+
+#[async_trait]
+pub trait TemplateStore: Send + Sync {
+    async fn save_template(&self, template: &WorkflowTemplate) -> AutomationResult<()>;
+    async fn load_template(&self, id: &str, version: u32) -> AutomationResult<Option<WorkflowTemplate>>;
+    async fn list_versions(&self, id: &str) -> AutomationResult<Vec<u32>>;
+    async fn save_instance(&self, instance: &TemplateInstance) -> AutomationResult<()>;
+    async fn list_instances(&self, template_id: &str) -> AutomationResult<Vec<TemplateInstance>>;
+}
+
+// Versions and instances as JSON objects under `workflow-templates/<id>/`
+pub struct ObjectTemplateStore {
+    objects: Arc<dyn ObjectStore>,
+}
+
+impl ObjectTemplateStore {
+    pub fn new(objects: Arc<dyn ObjectStore>) -> Self {
+        Self { objects }
+    }
+
+    fn versions_prefix(id: &str) -> String {
+        format!("{}{}/versions/", TEMPLATE_PREFIX, id)
+    }
+
+    fn instances_prefix(id: &str) -> String {
+        format!("{}{}/instances/", TEMPLATE_PREFIX, id)
+    }
+
+    async fn read<T: serde::de::DeserializeOwned>(&self, key: &str) -> AutomationResult<T> {
+        let data = self.objects.get(key).await?;
+        serde_json::from_slice(&data).map_err(|e| AutomationError::Internal(format!("Corrupt template object {}: {}", key, e)))
+    }
+
+    async fn write<T: Serialize>(&self, key: &str, value: &T) -> AutomationResult<()> {
+        let data = serde_json::to_vec(value).map_err(|e| AutomationError::Internal(e.to_string()))?;
+        self.objects.put(key, data).await
+    }
+}
+
+#[async_trait]
+impl TemplateStore for ObjectTemplateStore {
+    async fn save_template(&self, template: &WorkflowTemplate) -> AutomationResult<()> {
+        let key = format!("{}{:08}.json", Self::versions_prefix(&template.id), template.version);
+        self.write(&key, template).await
+    }
+
+    async fn load_template(&self, id: &str, version: u32) -> AutomationResult<Option<WorkflowTemplate>> {
+        let key = format!("{}{:08}.json", Self::versions_prefix(id), version);
+        if !self.objects.exists(&key).await? {
+            return Ok(None);
+        }
+        self.read(&key).await.map(Some)
+    }
+
+    async fn list_versions(&self, id: &str) -> AutomationResult<Vec<u32>> {
+        let prefix = Self::versions_prefix(id);
+        let mut versions: Vec<u32> = self
+            .objects
+            .list(&prefix)
+            .await?
+            .iter()
+            .filter_map(|key| key.strip_prefix(&prefix)?.strip_suffix(".json")?.parse().ok())
+            .collect();
+        versions.sort_unstable();
+        Ok(versions)
+    }
+
+    async fn save_instance(&self, instance: &TemplateInstance) -> AutomationResult<()> {
+        let key = format!("{}{}.json", Self::instances_prefix(&instance.lineage.template_id), instance.workflow.id);
+        self.write(&key, instance).await
+    }
+
+    async fn list_instances(&self, template_id: &str) -> AutomationResult<Vec<TemplateInstance>> {
+        let mut instances = Vec::new();
+        for key in self.objects.list(&Self::instances_prefix(template_id)).await? {
+            instances.push(self.read(&key).await?);
+        }
+        Ok(instances)
+    }
+}
+
+fn validate_template_id(id: &str) -> AutomationResult<()> {
+    let valid = !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(AutomationError::Validation(format!("Invalid template id '{}'", id)))
+    }
+}
+
+pub struct TemplateLibrary {
+    store: Arc<dyn TemplateStore>,
+}
+
+impl TemplateLibrary {
+    pub fn new(store: Arc<dyn TemplateStore>) -> Self {
+        Self { store }
+    }
+
+    // Stores the template as the next version. Every placeholder must name a declared parameter
+    // and every default must pass its own validation.
+    pub async fn publish_template(&self, mut template: WorkflowTemplate) -> AutomationResult<WorkflowTemplate> {
+        validate_template_id(&template.id)?;
+        let mut names = BTreeSet::new();
+        for parameter in &template.parameters {
+            if !names.insert(parameter.name.as_str()) {
+                return Err(AutomationError::Validation(format!("Duplicate template parameter '{}'", parameter.name)));
+            }
+            if let Some(default) = &parameter.default {
+                parameter.check(default).map_err(|e| {
+                    AutomationError::Validation(format!("Default for parameter '{}' {}", parameter.name, e))
+                })?;
+            }
+        }
+        // Only placeholder names matter here, so every parameter renders empty
+        template.render(&HashMap::new())?;
+
+        let latest = self.store.list_versions(&template.id).await?.last().copied().unwrap_or(0);
+        template.version = latest + 1;
+        template.published_at = Utc::now();
+        self.store.save_template(&template).await?;
+        Ok(template)
+    }
+
+    // The latest version when `version` is None
+    pub async fn get_template(&self, id: &str, version: Option<u32>) -> AutomationResult<WorkflowTemplate> {
+        let version = match version {
+            Some(version) => version,
+            None => self
+                .store
+                .list_versions(id)
+                .await?
+                .last()
+                .copied()
+                .ok_or_else(|| AutomationError::NotFound(format!("Workflow template {} not found", id)))?,
+        };
+        self.store
+            .load_template(id, version)
+            .await?
+            .ok_or_else(|| AutomationError::NotFound(format!("Workflow template {} version {} not found", id, version)))
+    }
+
+    pub async fn instantiate_template(
+        &self,
+        template_id: &str,
+        params: HashMap<String, Value>,
+        project_id: &str,
+    ) -> AutomationResult<Workflow> {
+        let template = self.get_template(template_id, None).await?;
+        let instance = self.render_instance(&template, &params, project_id, Uuid::new_v4().to_string())?;
+        self.store.save_instance(&instance).await?;
+        Ok(instance.workflow)
+    }
+
+    fn render_instance(
+        &self,
+        template: &WorkflowTemplate,
+        params: &HashMap<String, Value>,
+        project_id: &str,
+        workflow_id: String,
+    ) -> AutomationResult<TemplateInstance> {
+        let resolved = template.resolve(params)?;
+        let mut workflow = template.render(&resolved)?;
+        let now = Utc::now();
+        workflow.id = workflow_id;
+        workflow.version = template.version.to_string();
+        workflow.created_at = now;
+        workflow.updated_at = now;
+        workflow.metadata.insert(TEMPLATE_ID_KEY.into(), template.id.clone());
+        workflow.metadata.insert(TEMPLATE_VERSION_KEY.into(), template.version.to_string());
+        workflow.metadata.insert(PROJECT_ID_KEY.into(), project_id.to_string());
+        Ok(TemplateInstance {
+            workflow,
+            project_id: project_id.to_string(),
+            lineage: TemplateLineage {
+                template_id: template.id.clone(),
+                template_version: template.version,
+                parameters: resolved,
+            },
+            instantiated_at: now,
+        })
+    }
+
+    // Instances on an older version than the latest, with what re-instantiating each would change
+    pub async fn affected_instances(&self, template_id: &str) -> AutomationResult<Vec<InstanceDiff>> {
+        let latest = self.get_template(template_id, None).await?;
+        let mut diffs = Vec::new();
+        for instance in self.store.list_instances(template_id).await? {
+            if instance.lineage.template_version >= latest.version {
+                continue;
+            }
+            diffs.push(self.diff_instance(&latest, &instance)?);
+        }
+        diffs.sort_by(|a, b| a.workflow_id.cmp(&b.workflow_id));
+        Ok(diffs)
+    }
+
+    fn diff_instance(&self, latest: &WorkflowTemplate, instance: &TemplateInstance) -> AutomationResult<InstanceDiff> {
+        let mut diff = InstanceDiff {
+            workflow_id: instance.workflow.id.clone(),
+            project_id: instance.project_id.clone(),
+            from_version: instance.lineage.template_version,
+            to_version: latest.version,
+            workflow_fields: Vec::new(),
+            tasks: Vec::new(),
+            missing_parameters: latest
+                .parameters
+                .iter()
+                .filter(|p| p.required && p.default.is_none() && !instance.lineage.parameters.contains_key(&p.name))
+                .map(|p| p.name.clone())
+                .collect(),
+        };
+        if !diff.missing_parameters.is_empty() {
+            return Ok(diff);
+        }
+        let params = self.carried_parameters(latest, instance);
+        let updated = self.render_instance(latest, &params, &instance.project_id, instance.workflow.id.clone())?;
+        let json = |workflow: &Workflow| serde_json::to_value(workflow).map_err(|e| AutomationError::Internal(e.to_string()));
+        diff.workflow_fields = changed_fields(&json(&instance.workflow)?, &json(&updated.workflow)?, IGNORED_WORKFLOW_FIELDS);
+        diff.tasks = diff_tasks(&instance.workflow.tasks, &updated.workflow.tasks)?;
+        Ok(diff)
+    }
+
+    // Parameters the latest version no longer declares are dropped rather than rejected
+    fn carried_parameters(&self, latest: &WorkflowTemplate, instance: &TemplateInstance) -> HashMap<String, Value> {
+        instance
+            .lineage
+            .parameters
+            .iter()
+            .filter(|(name, _)| latest.parameters.iter().any(|p| &p.name == *name))
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect()
+    }
+
+    // Re-renders an instance from the latest version with its recorded parameters. The workflow
+    // keeps its id; apply it with `WorkflowManager::update_workflow`.
+    pub async fn reinstantiate(&self, template_id: &str, workflow_id: &str) -> AutomationResult<Workflow> {
+        let latest = self.get_template(template_id, None).await?;
+        let instance = self
+            .store
+            .list_instances(template_id)
+            .await?
+            .into_iter()
+            .find(|instance| instance.workflow.id == workflow_id)
+            .ok_or_else(|| AutomationError::NotFound(format!("Workflow {} was not instantiated from {}", workflow_id, template_id)))?;
+        let params = self.carried_parameters(&latest, &instance);
+        let mut updated = self.render_instance(&latest, &params, &instance.project_id, instance.workflow.id.clone())?;
+        updated.workflow.created_at = instance.workflow.created_at;
+        self.store.save_instance(&updated).await?;
+        Ok(updated.workflow)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::artifact::LocalObjectStore;
+    use crate::workflow::{ResourceRequirements, TaskConfig, TaskType, WorkflowStatus};
+
+    fn task(id: &str, task_type: TaskType, inputs: &[(&str, Value)]) -> Task {
+        Task {
+            id: id.to_string(),
+            name: id.to_string(),
+            task_type,
+            config: TaskConfig {
+                inputs: inputs.iter().map(|(k, v)| (k.to_string(), v.clone())).collect(),
+                environment: HashMap::new(),
+                resources: ResourceRequirements { cpu: "1".into(), memory: "1Gi".into(), storage: None, gpu: None },
+                secrets: Vec::new(),
+                artifacts: Vec::new(),
+            },
+            dependencies: Vec::new(),
+            retry_policy: None,
+            timeout: None,
+            on_failure: None,
+            conditions: Vec::new(),
+        }
+    }
+
+    fn workflow(tasks: Vec<Task>) -> Workflow {
+        Workflow {
+            id: "provision-template".into(),
+            name: "Provision {{ params.env }}".into(),
+            description: String::new(),
+            version: "0".into(),
+            tasks,
+            triggers: Vec::new(),
+            status: WorkflowStatus::Active,
+            schedule: None,
+            variables: HashMap::new(),
+            timeout: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            metadata: HashMap::new(),
+        }
+    }
+
+    fn provision_template(tasks: Vec<Task>) -> WorkflowTemplate {
+        WorkflowTemplate::new("provision-configure-notify", "Provision, configure, notify", workflow(tasks))
+            .with_parameter(
+                TemplateParameter::new("env", VariableType::String)
+                    .with_rule(ParameterRule::OneOf(vec!["staging".into(), "prod".into()])),
+            )
+            .with_parameter(
+                TemplateParameter::new("replicas", VariableType::Integer)
+                    .with_default(Value::Integer(2))
+                    .with_rule(ParameterRule::Min(1.0))
+                    .with_rule(ParameterRule::Max(10.0)),
+            )
+    }
+
+    fn base_tasks() -> Vec<Task> {
+        vec![
+            task(
+                "provision",
+                TaskType::AWS { service: "ec2".into(), action: "run-instances".into() },
+                &[
+                    ("count", Value::String("{{ params.replicas }}".into())),
+                    ("name", Value::String("web-{{ params.env }}".into())),
+                ],
+            ),
+            task(
+                "notify",
+                TaskType::Notification { channel: "slack".into() },
+                &[("message", Value::String("{{ params.env }} ready after {{ outputs.provision.id }}".into()))],
+            ),
+        ]
+    }
+
+    fn library(dir: &tempfile::TempDir) -> TemplateLibrary {
+        let objects = Arc::new(LocalObjectStore::new(dir.path()));
+        TemplateLibrary::new(Arc::new(ObjectTemplateStore::new(objects)))
+    }
+
+    #[tokio::test]
+    async fn test_instantiate_validates_parameters_and_placeholders() {
+        let dir = tempfile::tempdir().unwrap();
+        let library = library(&dir);
+        let template = library.publish_template(provision_template(base_tasks())).await.unwrap();
+        assert_eq!(template.version, 1);
+
+        let params = |pairs: &[(&str, Value)]| pairs.iter().map(|(k, v)| (k.to_string(), v.clone())).collect::<HashMap<_, _>>();
+        let instantiate = |p: HashMap<String, Value>| library.instantiate_template(&template.id, p, "proj-1");
+
+        let err = instantiate(params(&[("env", Value::String("prod".into())), ("replicas", Value::String("3".into()))]))
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("parameter 'replicas' expected Integer"), "{}", err);
+
+        let err = instantiate(params(&[("env", Value::String("dev".into())), ("replicas", Value::Integer(20)), ("region", Value::String("x".into()))]))
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("parameter 'env' violates OneOf"), "{}", err);
+        assert!(err.contains("parameter 'replicas' violates Max"), "{}", err);
+        assert!(err.contains("unknown parameter 'region'"), "{}", err);
+
+        assert!(instantiate(params(&[])).await.unwrap_err().to_string().contains("missing required parameter 'env'"));
+
+        let workflow = instantiate(params(&[("env", Value::String("prod".into()))])).await.unwrap();
+        assert_eq!(workflow.name, "Provision prod");
+        assert_eq!(workflow.metadata[TEMPLATE_VERSION_KEY], "1");
+        let inputs = &workflow.tasks[0].config.inputs;
+        assert!(matches!(inputs["count"], Value::Integer(2)));
+        assert!(matches!(&inputs["name"], Value::String(s) if s == "web-prod"));
+        // Run-time placeholders are left alone
+        assert!(matches!(&workflow.tasks[1].config.inputs["message"], Value::String(s) if s == "prod ready after {{ outputs.provision.id }}"));
+
+        // A placeholder naming an undeclared parameter is rejected before anything is stored
+        let mut tasks = base_tasks();
+        tasks[0].config.environment.insert("REGION".into(), "{{ params.region }}".into());
+        let err = library.publish_template(provision_template(tasks)).await.unwrap_err().to_string();
+        assert!(err.contains("undeclared parameters: region"), "{}", err);
+        assert_eq!(library.get_template(&template.id, None).await.unwrap().version, 1);
+    }
+
+    #[tokio::test]
+    async fn test_template_update_lists_affected_instances_with_diff() {
+        let dir = tempfile::tempdir().unwrap();
+        let library = library(&dir);
+        let template = library.publish_template(provision_template(base_tasks())).await.unwrap();
+        let env = |e: &str| HashMap::from([("env".to_string(), Value::String(e.into()))]);
+        let staging = library.instantiate_template(&template.id, env("staging"), "proj-1").await.unwrap();
+        let prod = library.instantiate_template(&template.id, env("prod"), "proj-2").await.unwrap();
+        assert!(library.affected_instances(&template.id).await.unwrap().is_empty());
+
+        // v2 changes provisioning, drops the notification and adds a configure step
+        let mut tasks = base_tasks();
+        tasks[0].config.inputs.insert("name".into(), Value::String("app-{{ params.env }}".into()));
+        tasks.pop();
+        tasks.push(task("configure", TaskType::Script { runtime: "bash".into() }, &[("target", Value::String("{{ params.env }}".into()))]));
+        let v2 = library.publish_template(provision_template(tasks)).await.unwrap();
+        assert_eq!(v2.version, 2);
+
+        let diffs = library.affected_instances(&template.id).await.unwrap();
+        assert_eq!(diffs.len(), 2);
+        let diff = diffs.iter().find(|d| d.workflow_id == prod.id).unwrap();
+        assert_eq!((diff.project_id.as_str(), diff.from_version, diff.to_version), ("proj-2", 1, 2));
+        assert!(diff.workflow_fields.is_empty());
+        assert!(diff.missing_parameters.is_empty());
+        assert_eq!(
+            diff.tasks,
+            vec![
+                TaskChange::Changed { task_id: "provision".into(), fields: vec!["config".into()] },
+                TaskChange::Added { task_id: "configure".into() },
+                TaskChange::Removed { task_id: "notify".into() },
+            ]
+        );
+
+        let updated = library.reinstantiate(&template.id, &staging.id).await.unwrap();
+        assert_eq!(updated.id, staging.id);
+        assert_eq!(updated.metadata[TEMPLATE_VERSION_KEY], "2");
+        assert!(matches!(&updated.tasks[0].config.inputs["name"], Value::String(s) if s == "app-staging"));
+        let remaining = library.affected_instances(&template.id).await.unwrap();
+        assert_eq!(remaining.iter().map(|d| d.workflow_id.as_str()).collect::<Vec<_>>(), vec![prod.id.as_str()]);
+    }
+}
@@ -0,0 +1,511 @@
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use chrono::Utc;
+use sirsi_common::Retryable;
+use tracing::{info, warn};
+
+use crate::artifact::{ArtifactManager, StoredArtifact};
+use crate::error::{AutomationError, AutomationResult};
+use super::admission::{AdmissionController, AdmissionRequest, ResourceQuantity};
+use super::{
+    DependencyType, ExecutionContext, FailureAction, ResourceUsage, RunMetrics, RunStatus, RunTrigger, Task,
+    TaskError, TaskExecutor, TaskMetrics, TaskResult, TaskRun, TriggerType, Value, Workflow, WorkflowRun,
+};
+
+// Runs a workflow's tasks sequentially in dependency order on this host
+pub struct LocalExecutor {
+    executors: HashMap<String, Arc<dyn TaskExecutor>>,
+    artifacts: Option<Arc<ArtifactManager>>,
+    admission: Option<Arc<AdmissionController>>,
+    work_root: PathBuf,
+}
+
+struct RunState {
+    workflow_id: String,
+    run_id: String,
+    variables: HashMap<String, Value>,
+    results: HashMap<String, TaskResult>,
+    produced: HashMap<String, StoredArtifact>,
+}
+
+impl LocalExecutor {
+    pub fn new(work_root: impl Into<PathBuf>) -> Self {
+        Self {
+            executors: HashMap::new(),
+            artifacts: None,
+            admission: None,
+            work_root: work_root.into(),
+        }
+    }
+
+    // Executors are keyed by `TaskType::kind`
+    pub fn with_executor(mut self, kind: impl Into<String>, executor: Arc<dyn TaskExecutor>) -> Self {
+        self.executors.insert(kind.into(), executor);
+        self
+    }
+
+    pub fn with_artifacts(mut self, artifacts: Arc<ArtifactManager>) -> Self {
+        self.artifacts = Some(artifacts);
+        self
+    }
+
+    // Tasks wait for their resource request to fit in the controller's pool before they start
+    pub fn with_admission(mut self, admission: Arc<AdmissionController>) -> Self {
+        self.admission = Some(admission);
+        self
+    }
+
+    pub async fn run(&self, workflow: &Workflow, inputs: HashMap<String, Value>) -> AutomationResult<WorkflowRun> {
+        let order = execution_order(workflow)?;
+        let mut state = RunState {
+            workflow_id: workflow.id.clone(),
+            run_id: uuid::Uuid::new_v4().to_string(),
+            variables: resolve_variables(workflow, inputs)?,
+            results: HashMap::new(),
+            produced: HashMap::new(),
+        };
+        let start_time = Utc::now();
+        let mut task_runs = Vec::with_capacity(order.len());
+        let mut aborted = false;
+
+        for task in order {
+            let task_start = Utc::now();
+            let outcome = if aborted || !dependencies_satisfied(task, &state.results) {
+                None
+            } else {
+                Some(self.run_task(task, &mut state).await)
+            };
+
+            let (status, outputs, error, metrics) = match outcome {
+                None => (RunStatus::Cancelled, HashMap::new(), None, empty_metrics()),
+                Some(Ok(result)) => {
+                    let record = (result.status.clone(), result.outputs.clone(), result.error.clone(), result.metrics.clone());
+                    state.results.insert(task.id.clone(), result);
+                    record
+                }
+                Some(Err(e)) => {
+                    warn!("Task {} in run {} failed: {}", task.id, state.run_id, e);
+                    let error = TaskError {
+                        code: e.kind().to_string(),
+                        message: e.to_string(),
+                        details: None,
+                        retry_count: 0,
+                    };
+                    let result = TaskResult {
+                        status: RunStatus::Failed,
+                        outputs: HashMap::new(),
+                        error: Some(error.clone()),
+                        metrics: empty_metrics(),
+                    };
+                    state.results.insert(task.id.clone(), result);
+                    (RunStatus::Failed, HashMap::new(), Some(error), empty_metrics())
+                }
+            };
+            if matches!(status, RunStatus::Failed) && !matches!(task.on_failure, Some(FailureAction::Continue)) {
+                aborted = true;
+            }
+
+            task_runs.push(TaskRun {
+                id: format!("{}-{}", state.run_id, task.id),
+                task_id: task.id.clone(),
+                status,
+                start_time: task_start,
+                end_time: Some(Utc::now()),
+                inputs: task.config.inputs.clone(),
+                outputs,
+                error,
+                logs_uri: None,
+                metrics,
+            });
+        }
+
+        let end_time = Utc::now();
+        if let Some(artifacts) = &self.artifacts {
+            artifacts.complete_run(&state.run_id, end_time).await?;
+        }
+        let failed_tasks = task_runs.iter().filter(|t| matches!(t.status, RunStatus::Failed)).count() as i32;
+        let metrics = RunMetrics {
+            total_duration_seconds: (end_time - start_time).num_seconds(),
+            task_count: task_runs.len() as i32,
+            failed_tasks,
+            retried_tasks: task_runs.iter().filter(|t| t.metrics.retry_count > 0).count() as i32,
+            resource_usage: ResourceUsage {
+                cpu_seconds: task_runs.iter().map(|t| t.metrics.resource_usage.cpu_seconds).sum(),
+                memory_mb_seconds: task_runs.iter().map(|t| t.metrics.resource_usage.memory_mb_seconds).sum(),
+                io_bytes: task_runs.iter().map(|t| t.metrics.resource_usage.io_bytes).sum(),
+            },
+        };
+
+        Ok(WorkflowRun {
+            id: state.run_id,
+            workflow_id: workflow.id.clone(),
+            version: workflow.version.clone(),
+            status: if failed_tasks > 0 { RunStatus::Failed } else { RunStatus::Succeeded },
+            trigger: RunTrigger {
+                type_: TriggerType::Event,
+                source: "local".to_string(),
+                event: None,
+            },
+            task_runs,
+            variables: state.variables,
+            start_time,
+            end_time: Some(end_time),
+            metrics,
+        })
+    }
+
+    async fn run_task(&self, task: &Task, state: &mut RunState) -> AutomationResult<TaskResult> {
+        let working_dir = self.work_root.join(&state.run_id).join(&task.id);
+        tokio::fs::create_dir_all(&working_dir)
+            .await
+            .map_err(|e| AutomationError::Internal(format!("Failed to create {}: {}", working_dir.display(), e)))?;
+
+        let mut cache_key = None;
+        if let Some(artifacts) = &self.artifacts {
+            let inputs = artifacts.download_inputs(task, &working_dir, &state.produced).await?;
+            cache_key = artifacts.cache_key(task, &inputs);
+            if let Some(key) = &cache_key {
+                if let Some(entry) = artifacts.lookup_cache(task, key).await? {
+                    info!("Cache hit for task {} in run {}, skipping execution", task.id, state.run_id);
+                    let restored = artifacts.restore_cache(&state.run_id, task, &entry, &working_dir).await?;
+                    publish(&mut state.produced, restored)?;
+                    return Ok(TaskResult {
+                        status: RunStatus::Succeeded,
+                        outputs: entry.outputs,
+                        error: None,
+                        metrics: empty_metrics(),
+                    });
+                }
+            }
+        }
+
+        let kind = task.task_type.kind();
+        let executor = self
+            .executors
+            .get(kind)
+            .ok_or_else(|| AutomationError::Config(format!("No executor registered for {} tasks", kind)))?;
+        let context = ExecutionContext {
+            workflow_run_id: state.run_id.clone(),
+            task_run_id: format!("{}-{}", state.run_id, task.id),
+            variables: state.variables.clone(),
+            previous_results: state.results.clone(),
+            working_dir: working_dir.clone(),
+        };
+        let admitted = match &self.admission {
+            Some(admission) => {
+                let resources = ResourceQuantity::from_requirements(&task.config.resources)?;
+                let request = AdmissionRequest {
+                    workflow_id: state.workflow_id.clone(),
+                    run_id: state.run_id.clone(),
+                    task_id: task.id.clone(),
+                    resources,
+                };
+                Some((admission, admission.admit(request).await?))
+            }
+            None => None,
+        };
+        let result = executor.execute_task(task.clone(), context).await?;
+        if let Some((admission, reservation)) = admitted {
+            admission.record_usage(&state.workflow_id, &task.id, reservation.request().resources, &result.metrics);
+        }
+
+        if let (Some(artifacts), RunStatus::Succeeded) = (&self.artifacts, &result.status) {
+            let uploaded = artifacts.upload_outputs(&state.run_id, task, &working_dir).await?;
+            publish(&mut state.produced, uploaded)?;
+            if let Some(key) = &cache_key {
+                artifacts.save_cache(task, key, &result.outputs, &working_dir).await?;
+            }
+        }
+        Ok(result)
+    }
+}
+
+// Artifact names are the join key between producers and consumers, so they must be unique per run
+fn publish(produced: &mut HashMap<String, StoredArtifact>, artifacts: Vec<StoredArtifact>) -> AutomationResult<()> {
+    for artifact in artifacts {
+        if let Some(existing) = produced.get(&artifact.name) {
+            return Err(AutomationError::Conflict(format!(
+                "Artifact {} produced by both {} and {}",
+                artifact.name, existing.task_id, artifact.task_id
+            )));
+        }
+        produced.insert(artifact.name.clone(), artifact);
+    }
+    Ok(())
+}
+
+fn dependencies_satisfied(task: &Task, results: &HashMap<String, TaskResult>) -> bool {
+    task.dependencies.iter().all(|dependency| {
+        let Some(result) = results.get(&dependency.task_id) else {
+            return false;
+        };
+        match &dependency.type_ {
+            DependencyType::Success => matches!(result.status, RunStatus::Succeeded),
+            DependencyType::Failure => matches!(result.status, RunStatus::Failed),
+            DependencyType::Completed => true,
+            DependencyType::Data { key } => {
+                matches!(result.status, RunStatus::Succeeded) && result.outputs.contains_key(key)
+            }
+        }
+    })
+}
+
+fn execution_order(workflow: &Workflow) -> AutomationResult<Vec<&Task>> {
+    let index: HashMap<&str, usize> = workflow.tasks.iter().enumerate().map(|(i, t)| (t.id.as_str(), i)).collect();
+    let mut in_degree = vec![0usize; workflow.tasks.len()];
+    let mut dependents: Vec<Vec<usize>> = vec![Vec::new(); workflow.tasks.len()];
+    for (i, task) in workflow.tasks.iter().enumerate() {
+        for dependency in &task.dependencies {
+            let upstream = *index.get(dependency.task_id.as_str()).ok_or_else(|| {
+                AutomationError::Validation(format!(
+                    "Task {} depends on unknown task {}",
+                    task.id, dependency.task_id
+                ))
+            })?;
+            in_degree[i] += 1;
+            dependents[upstream].push(i);
+        }
+    }
+
+    // Kahn's algorithm, seeded in declaration order so independent tasks keep their relative order
+    let mut ready: VecDeque<usize> = (0..workflow.tasks.len()).filter(|i| in_degree[*i] == 0).collect();
+    let mut order = Vec::with_capacity(workflow.tasks.len());
+    while let Some(i) = ready.pop_front() {
+        order.push(&workflow.tasks[i]);
+        for &next in &dependents[i] {
+            in_degree[next] -= 1;
+            if in_degree[next] == 0 {
+                ready.push_back(next);
+            }
+        }
+    }
+    if order.len() != workflow.tasks.len() {
+        return Err(AutomationError::Validation(format!(
+            "Workflow {} has a dependency cycle",
+            workflow.id
+        )));
+    }
+    Ok(order)
+}
+
+fn resolve_variables(workflow: &Workflow, mut inputs: HashMap<String, Value>) -> AutomationResult<HashMap<String, Value>> {
+    let mut variables = HashMap::new();
+    for (name, variable) in &workflow.variables {
+        match inputs
+            .remove(name)
+            .or_else(|| variable.value.clone())
+            .or_else(|| variable.default.clone())
+        {
+            Some(value) => {
+                variables.insert(name.clone(), value);
+            }
+            None if variable.required => {
+                return Err(AutomationError::Validation(format!("Missing required variable {}", name)));
+            }
+            None => {}
+        }
+    }
+    variables.extend(inputs);
+    Ok(variables)
+}
+
+fn empty_metrics() -> TaskMetrics {
+    TaskMetrics {
+        duration_seconds: 0,
+        retry_count: 0,
+        resource_usage: ResourceUsage {
+            cpu_seconds: 0.0,
+            memory_mb_seconds: 0.0,
+            io_bytes: 0,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::artifact::{ArtifactConfig, LocalObjectStore};
+    use crate::workflow::{Artifact, ArtifactType, ResourceRequirements, TaskConfig, TaskDependency, TaskType, WorkflowStatus};
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // "build" writes a bundle into its working directory; "deploy" reads back whatever it was given
+    #[derive(Default)]
+    struct ScriptStub {
+        builds: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl TaskExecutor for ScriptStub {
+        async fn execute_task(&self, task: Task, context: ExecutionContext) -> AutomationResult<TaskResult> {
+            let mut outputs = HashMap::new();
+            match task.id.as_str() {
+                "build" => {
+                    self.builds.fetch_add(1, Ordering::SeqCst);
+                    let out = context.working_dir.join("out");
+                    tokio::fs::create_dir_all(&out).await.unwrap();
+                    let bundle: Vec<u8> = (0..=255u8).cycle().take(4096).collect();
+                    tokio::fs::write(out.join("bundle.tar"), bundle).await.unwrap();
+                    outputs.insert("version".to_string(), Value::String("1.0.0".into()));
+                }
+                _ => {
+                    let bundle = tokio::fs::read(context.working_dir.join("in/bundle.tar")).await.map_err(|e| {
+                        AutomationError::Task(format!("bundle missing: {}", e))
+                    })?;
+                    outputs.insert("bytes".to_string(), Value::Integer(bundle.len() as i64));
+                    outputs.insert(
+                        "intact".to_string(),
+                        Value::Boolean(bundle.iter().enumerate().all(|(i, b)| *b == (i % 256) as u8)),
+                    );
+                }
+            }
+            Ok(TaskResult {
+                status: RunStatus::Succeeded,
+                outputs,
+                error: None,
+                metrics: empty_metrics(),
+            })
+        }
+
+        async fn validate_task(&self, _task: &Task) -> AutomationResult<()> {
+            Ok(())
+        }
+
+        async fn abort_task(&self, _task_run_id: &str) -> AutomationResult<()> {
+            Ok(())
+        }
+    }
+
+    fn task(id: &str, artifacts: Vec<Artifact>, dependencies: &[&str]) -> Task {
+        Task {
+            id: id.to_string(),
+            name: id.to_string(),
+            task_type: TaskType::Script { runtime: "bash".into() },
+            config: TaskConfig {
+                inputs: HashMap::from([("target".to_string(), Value::String("linux".into()))]),
+                environment: HashMap::new(),
+                resources: ResourceRequirements {
+                    cpu: "1".into(),
+                    memory: "1Gi".into(),
+                    storage: None,
+                    gpu: None,
+                },
+                secrets: Vec::new(),
+                artifacts,
+            },
+            dependencies: dependencies
+                .iter()
+                .map(|d| TaskDependency {
+                    task_id: d.to_string(),
+                    type_: DependencyType::Success,
+                    condition: None,
+                })
+                .collect(),
+            retry_policy: None,
+            timeout: None,
+            on_failure: None,
+            conditions: Vec::new(),
+        }
+    }
+
+    fn workflow(build_artifact: ArtifactType) -> Workflow {
+        Workflow {
+            id: "release".into(),
+            name: "release".into(),
+            description: String::new(),
+            version: "1".into(),
+            // Declared out of order on purpose; execution must follow dependencies
+            tasks: vec![
+                task(
+                    "deploy",
+                    vec![Artifact {
+                        name: "bundle".into(),
+                        path: "in/bundle.tar".into(),
+                        type_: ArtifactType::Input,
+                    }],
+                    &["build"],
+                ),
+                task(
+                    "build",
+                    vec![Artifact {
+                        name: "bundle".into(),
+                        path: "/workspace/out/bundle.tar".into(),
+                        type_: build_artifact,
+                    }],
+                    &[],
+                ),
+            ],
+            triggers: Vec::new(),
+            status: WorkflowStatus::Active,
+            schedule: None,
+            variables: HashMap::new(),
+            timeout: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            metadata: HashMap::new(),
+        }
+    }
+
+    fn output<'a>(run: &'a WorkflowRun, task_id: &str, key: &str) -> &'a Value {
+        &run.task_runs.iter().find(|t| t.task_id == task_id).unwrap().outputs[key]
+    }
+
+    #[tokio::test]
+    async fn test_artifact_flows_between_tasks_and_cache_skips_producer() {
+        let root = tempfile::tempdir().unwrap();
+        let store = Arc::new(LocalObjectStore::new(root.path().join("store")));
+        let artifacts = Arc::new(ArtifactManager::new(store, ArtifactConfig::default()));
+        let stub = Arc::new(ScriptStub::default());
+        let executor = LocalExecutor::new(root.path().join("work"))
+            .with_executor("script", stub.clone())
+            .with_artifacts(artifacts);
+        let workflow = workflow(ArtifactType::Cache);
+
+        let first = executor.run(&workflow, HashMap::new()).await.unwrap();
+        assert!(matches!(first.status, RunStatus::Succeeded), "{:?}", first.task_runs);
+        assert_eq!(first.task_runs[0].task_id, "build");
+        assert!(matches!(output(&first, "deploy", "bytes"), Value::Integer(4096)));
+        assert!(matches!(output(&first, "deploy", "intact"), Value::Boolean(true)));
+        assert_eq!(stub.builds.load(Ordering::SeqCst), 1);
+
+        let second = executor.run(&workflow, HashMap::new()).await.unwrap();
+        assert!(matches!(second.status, RunStatus::Succeeded), "{:?}", second.task_runs);
+        assert_eq!(stub.builds.load(Ordering::SeqCst), 1);
+        assert!(matches!(output(&second, "build", "version"), Value::String(v) if v == "1.0.0"));
+        assert!(matches!(output(&second, "deploy", "intact"), Value::Boolean(true)));
+    }
+
+    #[tokio::test]
+    async fn test_missing_output_fails_run_and_cancels_dependents() {
+        let root = tempfile::tempdir().unwrap();
+        let store = Arc::new(LocalObjectStore::new(root.path().join("store")));
+        let artifacts = Arc::new(ArtifactManager::new(store, ArtifactConfig::default()));
+        let executor = LocalExecutor::new(root.path().join("work"))
+            .with_executor("script", Arc::new(ScriptStub::default()))
+            .with_artifacts(artifacts);
+
+        let mut workflow = workflow(ArtifactType::Output);
+        workflow.tasks[1].config.artifacts[0].path = "missing.tar".into();
+
+        let run = executor.run(&workflow, HashMap::new()).await.unwrap();
+        assert!(matches!(run.status, RunStatus::Failed));
+        assert!(matches!(run.task_runs[0].status, RunStatus::Failed));
+        assert_eq!(run.task_runs[0].error.as_ref().unwrap().code, "not_found");
+        assert!(matches!(run.task_runs[1].status, RunStatus::Cancelled));
+    }
+
+    #[tokio::test]
+    async fn test_dependency_cycle_is_rejected() {
+        let mut workflow = workflow(ArtifactType::Output);
+        workflow.tasks[1].dependencies = workflow.tasks[0].dependencies.clone();
+        workflow.tasks[1].dependencies[0].task_id = "deploy".into();
+
+        let err = LocalExecutor::new(std::env::temp_dir())
+            .run(&workflow, HashMap::new())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("cycle"));
+    }
+}
@@ -1,10 +1,21 @@
 use std::collections::HashMap;
+use std::path::PathBuf;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
 
 use crate::error::AutomationResult;
 
+pub mod admission;
+pub mod graph;
+pub mod library;
+pub mod local;
+
+pub use admission::{AdmissionController, CapacitySource, ResourceQuantity};
+pub use graph::{GraphExporter, GraphFormat};
+pub use library::{ObjectTemplateStore, TemplateLibrary, TemplateStore, WorkflowTemplate};
+pub use local::LocalExecutor;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Workflow {
     pub id: String,
@@ -50,6 +61,25 @@ pub enum TaskType {
     Notification { channel: String },
 }
 
+impl TaskType {
+    // Stable name used to route tasks to an executor
+    pub fn kind(&self) -> &'static str {
+        match self {
+            TaskType::Script { .. } => "script",
+            TaskType::Container { .. } => "container",
+            TaskType::Function { .. } => "function",
+            TaskType::HTTP { .. } => "http",
+            TaskType::AWS { .. } => "aws",
+            TaskType::GCP { .. } => "gcp",
+            TaskType::Azure { .. } => "azure",
+            TaskType::Kubernetes { .. } => "kubernetes",
+            TaskType::Database { .. } => "database",
+            TaskType::Queue { .. } => "queue",
+            TaskType::Notification { .. } => "notification",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TaskConfig {
     pub inputs: HashMap<String, Value>,
@@ -329,6 +359,8 @@ pub trait WorkflowManager: Send + Sync {
     async fn get_workflow(&self, id: &str) -> AutomationResult<Workflow>;
     async fn list_workflows(&self) -> AutomationResult<Vec<Workflow>>;
     async fn start_workflow(&self, id: &str, inputs: HashMap<String, Value>) -> AutomationResult<WorkflowRun>;
+    // Returns the existing run instead of starting a new one when the key has been seen before
+    async fn start_workflow_with_key(&self, id: &str, inputs: HashMap<String, Value>, idempotency_key: &str) -> AutomationResult<WorkflowRun>;
     async fn stop_workflow(&self, run_id: &str) -> AutomationResult<()>;
     async fn get_workflow_run(&self, run_id: &str) -> AutomationResult<WorkflowRun>;
     async fn list_workflow_runs(&self, workflow_id: &str) -> AutomationResult<Vec<WorkflowRun>>;
@@ -347,6 +379,7 @@ pub struct ExecutionContext {
     pub task_run_id: String,
     pub variables: HashMap<String, Value>,
     pub previous_results: HashMap<String, TaskResult>,
+    pub working_dir: PathBuf,
 }
 
 #[derive(Debug, Clone)]
@@ -1,4 +1,5 @@
 use aws_config::SdkConfig;
+use aws_sdk_ec2::config::Credentials as StaticCredentials;
 use aws_sdk_ec2::Client as Ec2Client;
 use aws_sdk_rds::Client as RdsClient;
 use aws_sdk_s3::Client as S3Client;
@@ -6,6 +7,8 @@ use aws_sdk_iam::Client as IamClient;
 use aws_sdk_eks::Client as EksClient;
 use aws_sdk_cloudformation::Client as CloudFormationClient;
 
+use sirsi_key_vault::{secret::SecretManager, KeyVaultError};
+
 use crate::error::{AgentError, AgentResult};
 use super::config::AwsConfig;
 
@@ -21,10 +24,28 @@ pub struct AwsClient {
 
 impl AwsClient {
     pub async fn new(aws_config: &AwsConfig) -> AgentResult<Self> {
-        let config = aws_config::from_env()
-            .region(aws_config.region.clone())
-            .load()
-            .await;
+        Self::with_secrets(aws_config, None).await
+    }
+
+    // Static credentials in the config are fetched from key-vault on every call, so a client
+    // built after a rotation uses the new keys
+    pub async fn with_secrets(aws_config: &AwsConfig, secrets: Option<&dyn SecretManager>) -> AgentResult<Self> {
+        let mut loader = aws_config::from_env().region(aws_config.region.clone());
+        if let Some(credentials) = &aws_config.credentials {
+            let secret_access_key = credentials.secret_access_key.resolve(secrets).await.map_err(vault_error)?;
+            let session_token = match &credentials.session_token {
+                Some(token) => Some(token.resolve(secrets).await.map_err(vault_error)?),
+                None => None,
+            };
+            loader = loader.credentials_provider(StaticCredentials::new(
+                credentials.access_key_id.clone(),
+                secret_access_key.expose(),
+                session_token.as_ref().map(|token| token.expose().to_string()),
+                None,
+                "sirsi-key-vault",
+            ));
+        }
+        let config = loader.load().await;
 
         Ok(Self {
             ec2: Ec2Client::new(&config),
@@ -66,6 +87,13 @@ impl AwsClient {
     }
 }
 
+fn vault_error(e: KeyVaultError) -> AgentError {
+    match e {
+        KeyVaultError::Permission(msg) => AgentError::Auth(msg),
+        e => AgentError::Auth(format!("Failed to resolve AWS credentials: {}", e)),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use sirsi_key_vault::Credential;
 use std::collections::HashMap;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -16,12 +17,14 @@ pub struct AwsConfig {
     pub max_retries: Option<u32>,
 }
 
+// Static keys; the secret parts are key-vault references (`vault:<secret id>`) resolved when
+// the client is built
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AwsCredentials {
     pub access_key_id: String,
-    pub secret_access_key: String,
+    pub secret_access_key: Credential,
     #[serde(default)]
-    pub session_token: Option<String>,
+    pub session_token: Option<Credential>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -56,7 +59,7 @@ mod tests {
             profile: Some("default".to_string()),
             credentials: Some(AwsCredentials {
                 access_key_id: "test_key".to_string(),
-                secret_access_key: "test_secret".to_string(),
+                secret_access_key: Credential::vault("aws/test/secret_access_key"),
                 session_token: None,
             }),
             endpoints: {
@@ -79,6 +82,7 @@ mod tests {
         assert_eq!(config.region, deserialized.region);
         assert_eq!(config.profile, deserialized.profile);
         assert_eq!(config.max_retries, deserialized.max_retries);
+        assert!(json.contains("vault:aws/test/secret_access_key"));
         assert_eq!(
             config.endpoints.get("s3"),
             deserialized.endpoints.get("s3")
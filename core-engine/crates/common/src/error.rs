@@ -0,0 +1,195 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Provider-agnostic classification shared by every crate's error type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    Throttled,
+    AuthFailure,
+    NotFound,
+    Conflict,
+    InvalidInput,
+    ProviderOutage,
+    Internal,
+}
+
+impl ErrorKind {
+    /// Transient failures worth retrying with backoff.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, ErrorKind::Throttled | ErrorKind::ProviderOutage)
+    }
+
+    /// Status code to surface when the failure comes from an upstream provider.
+    pub fn http_status(&self) -> u16 {
+        match self {
+            ErrorKind::Throttled => 429,
+            ErrorKind::AuthFailure => 502,
+            ErrorKind::NotFound => 404,
+            ErrorKind::Conflict => 409,
+            ErrorKind::InvalidInput => 400,
+            ErrorKind::ProviderOutage => 503,
+            ErrorKind::Internal => 502,
+        }
+    }
+
+    pub fn from_http_status(status: u16) -> Self {
+        match status {
+            400 | 413 | 415 | 422 => ErrorKind::InvalidInput,
+            401 | 403 => ErrorKind::AuthFailure,
+            404 | 410 => ErrorKind::NotFound,
+            409 | 412 => ErrorKind::Conflict,
+            429 => ErrorKind::Throttled,
+            408 | 502 | 503 | 504 => ErrorKind::ProviderOutage,
+            _ => ErrorKind::Internal,
+        }
+    }
+
+    /// Maps AWS error codes as reported by `ProvideErrorMetadata::code()`.
+    pub fn from_aws_code(code: &str) -> Self {
+        match code {
+            "Throttling"
+            | "ThrottlingException"
+            | "ThrottledException"
+            | "RequestLimitExceeded"
+            | "RequestThrottled"
+            | "RequestThrottledException"
+            | "TooManyRequestsException"
+            | "ProvisionedThroughputExceededException"
+            | "SlowDown"
+            | "PriorRequestNotComplete"
+            | "BandwidthLimitExceeded"
+            | "EC2ThrottledException" => ErrorKind::Throttled,
+            "ServiceUnavailable"
+            | "ServiceUnavailableException"
+            | "InternalFailure"
+            | "InternalError"
+            | "InternalServerError"
+            | "RequestTimeout"
+            | "RequestTimeoutException"
+            | "Unavailable"
+            | "InsufficientInstanceCapacity" => ErrorKind::ProviderOutage,
+            "AuthFailure"
+            | "UnauthorizedOperation"
+            | "UnrecognizedClientException"
+            | "InvalidClientTokenId"
+            | "SignatureDoesNotMatch"
+            | "ExpiredToken"
+            | "ExpiredTokenException"
+            | "AccessDenied"
+            | "AccessDeniedException" => ErrorKind::AuthFailure,
+            "ResourceInUseException"
+            | "ResourceConflictException"
+            | "ConflictException"
+            | "AlreadyExists"
+            | "AlreadyExistsFault"
+            | "IncorrectState"
+            | "ScalingActivityInProgress" => ErrorKind::Conflict,
+            "ValidationError"
+            | "ValidationException"
+            | "InvalidParameter"
+            | "InvalidParameterValue"
+            | "InvalidParameterCombination"
+            | "InvalidParameterValueException"
+            | "MissingParameter" => ErrorKind::InvalidInput,
+            code if code.ends_with(".Malformed") => ErrorKind::InvalidInput,
+            code if code.ends_with("NotFound")
+                || code.ends_with("NotFoundException")
+                || code.starts_with("NoSuch") =>
+            {
+                ErrorKind::NotFound
+            }
+            code if code.ends_with(".Duplicate") || code.ends_with(".InUse") => ErrorKind::Conflict,
+            _ => ErrorKind::Internal,
+        }
+    }
+
+    /// Maps Google API errors, which carry both an HTTP code and a canonical status string.
+    pub fn from_gcp_status(http_code: Option<u16>, status: Option<&str>) -> Self {
+        if let Some(status) = status {
+            let kind = match status {
+                "RESOURCE_EXHAUSTED" => Some(ErrorKind::Throttled),
+                "UNAVAILABLE" | "DEADLINE_EXCEEDED" => Some(ErrorKind::ProviderOutage),
+                "UNAUTHENTICATED" | "PERMISSION_DENIED" => Some(ErrorKind::AuthFailure),
+                "NOT_FOUND" => Some(ErrorKind::NotFound),
+                "ALREADY_EXISTS" | "ABORTED" | "FAILED_PRECONDITION" => Some(ErrorKind::Conflict),
+                "INVALID_ARGUMENT" | "OUT_OF_RANGE" => Some(ErrorKind::InvalidInput),
+                _ => None,
+            };
+            if let Some(kind) = kind {
+                return kind;
+            }
+        }
+
+        http_code.map(Self::from_http_status).unwrap_or(ErrorKind::Internal)
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ErrorKind::Throttled => "throttled",
+            ErrorKind::AuthFailure => "auth_failure",
+            ErrorKind::NotFound => "not_found",
+            ErrorKind::Conflict => "conflict",
+            ErrorKind::InvalidInput => "invalid_input",
+            ErrorKind::ProviderOutage => "provider_outage",
+            ErrorKind::Internal => "internal",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Implemented by crate error types so callers can tell transient from permanent failures.
+pub trait Retryable {
+    fn kind(&self) -> ErrorKind;
+
+    fn is_retryable(&self) -> bool {
+        self.kind().is_retryable()
+    }
+}
+
+impl Retryable for ErrorKind {
+    fn kind(&self) -> ErrorKind {
+        *self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aws_codes() {
+        assert_eq!(ErrorKind::from_aws_code("Throttling"), ErrorKind::Throttled);
+        assert_eq!(ErrorKind::from_aws_code("RequestLimitExceeded"), ErrorKind::Throttled);
+        assert_eq!(ErrorKind::from_aws_code("InvalidInstanceID.NotFound"), ErrorKind::NotFound);
+        assert_eq!(ErrorKind::from_aws_code("InvalidInstanceID.Malformed"), ErrorKind::InvalidInput);
+        assert_eq!(ErrorKind::from_aws_code("InvalidGroup.Duplicate"), ErrorKind::Conflict);
+        assert_eq!(ErrorKind::from_aws_code("UnauthorizedOperation"), ErrorKind::AuthFailure);
+        assert_eq!(ErrorKind::from_aws_code("ServiceUnavailable"), ErrorKind::ProviderOutage);
+        assert_eq!(ErrorKind::from_aws_code("SomethingElse"), ErrorKind::Internal);
+    }
+
+    #[test]
+    fn test_gcp_status() {
+        assert_eq!(ErrorKind::from_gcp_status(Some(429), None), ErrorKind::Throttled);
+        assert_eq!(ErrorKind::from_gcp_status(Some(503), None), ErrorKind::ProviderOutage);
+        assert_eq!(
+            ErrorKind::from_gcp_status(Some(403), Some("RESOURCE_EXHAUSTED")),
+            ErrorKind::Throttled
+        );
+        assert_eq!(ErrorKind::from_gcp_status(Some(400), Some("UNKNOWN")), ErrorKind::InvalidInput);
+        assert_eq!(ErrorKind::from_gcp_status(None, None), ErrorKind::Internal);
+    }
+
+    #[test]
+    fn test_http_status_round_trip() {
+        assert_eq!(ErrorKind::from_http_status(429).http_status(), 429);
+        assert_eq!(ErrorKind::from_http_status(503).http_status(), 503);
+        assert_eq!(ErrorKind::from_http_status(500).http_status(), 502);
+        assert!(ErrorKind::from_http_status(504).is_retryable());
+        assert!(!ErrorKind::from_http_status(404).is_retryable());
+    }
+}
@@ -0,0 +1,6 @@
+// Shared building blocks used across the SirsiNexus crates
+pub mod error;
+pub mod retry;
+
+pub use error::{ErrorKind, Retryable};
+pub use retry::{retry, RetryPolicy};
@@ -0,0 +1,120 @@
+use std::future::Future;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::error::Retryable;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    pub multiplier: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(20),
+            multiplier: 2.0,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new(max_attempts: u32) -> Self {
+        Self {
+            max_attempts,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+        self
+    }
+
+    pub fn with_multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier;
+        self
+    }
+
+    /// Delay before the given retry (1-based).
+    pub fn backoff_for(&self, retry: u32) -> Duration {
+        let factor = self.multiplier.powi(retry.saturating_sub(1) as i32);
+        let delay = self.initial_backoff.mul_f64(factor);
+        delay.min(self.max_backoff)
+    }
+}
+
+/// Runs `op` until it succeeds, returns a non-retryable error, or the policy is exhausted.
+pub async fn retry<T, E, F, Fut>(policy: &RetryPolicy, mut op: F) -> Result<T, E>
+where
+    E: Retryable + std::fmt::Display,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut attempt = 1;
+    loop {
+        match op().await {
+            Ok(value) => return Ok(value),
+            Err(e) if e.is_retryable() && attempt < policy.max_attempts => {
+                let delay = policy.backoff_for(attempt);
+                warn!(
+                    "Attempt {} failed with {} error, retrying in {:?}: {}",
+                    attempt,
+                    e.kind(),
+                    delay,
+                    e
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorKind;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[test]
+    fn test_backoff_is_capped() {
+        let policy = RetryPolicy::new(10)
+            .with_backoff(Duration::from_millis(100), Duration::from_millis(500));
+        assert_eq!(policy.backoff_for(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff_for(3), Duration::from_millis(400));
+        assert_eq!(policy.backoff_for(6), Duration::from_millis(500));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retries_only_transient_errors() {
+        let policy = RetryPolicy::new(3);
+
+        let calls = AtomicU32::new(0);
+        let result: Result<(), ErrorKind> = retry(&policy, || {
+            calls.fetch_add(1, Ordering::SeqCst);
+            async { Err(ErrorKind::Throttled) }
+        })
+        .await;
+        assert_eq!(result, Err(ErrorKind::Throttled));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        let calls = AtomicU32::new(0);
+        let result: Result<(), ErrorKind> = retry(&policy, || {
+            calls.fetch_add(1, Ordering::SeqCst);
+            async { Err(ErrorKind::NotFound) }
+        })
+        .await;
+        assert_eq!(result, Err(ErrorKind::NotFound));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+}
@@ -4,7 +4,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         .build_server(true)
         .build_client(true)
         .compile(
-            &["proto/compute.proto"],
+            &["proto/compute.proto", "proto/provider.proto"],
             &["proto"],
         )?;
     Ok(())
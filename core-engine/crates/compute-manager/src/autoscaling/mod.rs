@@ -2,6 +2,10 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use chrono::{DateTime, Utc};
 
+pub mod signal;
+
+pub use signal::{MetricSignal, ScalingRecommendation, ScalingSignalAdapter};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AutoScalingConfig {
     pub id: String,
@@ -25,6 +29,7 @@ pub enum ResourceType {
     Function,
     Database,
     Cache,
+    MlDeployment,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -0,0 +1,371 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sirsi_common::Retryable;
+use sirsi_observability::monitoring::{AggregationType, MetricDataPoint, MetricQuery, MetricValue, MetricsManager};
+use tracing::{debug, info};
+
+use crate::error::{ComputeError, ComputeResult};
+use super::{AutoScalingConfig, ComparisonOperator, MetricStatistic, ResourceType, ScalingMetric};
+
+// Weight of the newest sample; lower values smooth harder and react later
+pub const DEFAULT_SMOOTHING: f64 = 0.3;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetricSignal {
+    pub metric: String,
+    pub namespace: String,
+    pub raw: f64,
+    pub smoothed: f64,
+    pub breaching: bool,
+    // Whether enough of the last evaluation periods breached to trigger `scale_adjustment`
+    pub alarm: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScalingRecommendation {
+    pub config_id: String,
+    pub resource_id: String,
+    pub resource_type: ResourceType,
+    pub current_capacity: i32,
+    pub desired_capacity: i32,
+    pub signals: Vec<MetricSignal>,
+    pub reason: String,
+    pub evaluated_at: DateTime<Utc>,
+}
+
+#[derive(Default)]
+struct SignalState {
+    smoothed: Option<f64>,
+    breaches: VecDeque<bool>,
+}
+
+fn aggregation(statistic: &MetricStatistic) -> AggregationType {
+    match statistic {
+        MetricStatistic::Average => AggregationType::Average,
+        MetricStatistic::Sum => AggregationType::Sum,
+        MetricStatistic::Minimum => AggregationType::Minimum,
+        MetricStatistic::Maximum => AggregationType::Maximum,
+        MetricStatistic::SampleCount => AggregationType::Count,
+        MetricStatistic::Percentile(p) => AggregationType::Percentile(*p),
+    }
+}
+
+fn values(point: &MetricDataPoint) -> Vec<f64> {
+    match &point.value {
+        MetricValue::Single(v) => vec![*v],
+        MetricValue::Multiple(vs) => vs.clone(),
+        MetricValue::Distribution { sum, count, .. } if *count > 0 => vec![sum / *count as f64],
+        MetricValue::Distribution { .. } => Vec::new(),
+    }
+}
+
+// Combines every sample in the period, e.g. lag across all partitions of a queue
+fn reduce(statistic: &MetricStatistic, mut samples: Vec<f64>) -> Option<f64> {
+    if samples.is_empty() {
+        return None;
+    }
+    let n = samples.len() as f64;
+    Some(match statistic {
+        MetricStatistic::Average => samples.iter().sum::<f64>() / n,
+        MetricStatistic::Sum => samples.iter().sum(),
+        MetricStatistic::Minimum => samples.iter().copied().fold(f64::INFINITY, f64::min),
+        MetricStatistic::Maximum => samples.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        MetricStatistic::SampleCount => n,
+        MetricStatistic::Percentile(p) => {
+            samples.sort_by(f64::total_cmp);
+            let rank = ((p / 100.0) * n).ceil().max(1.0) as usize;
+            samples[rank.min(samples.len()) - 1]
+        }
+    })
+}
+
+fn breaches(comparison: &ComparisonOperator, value: f64, threshold: f64) -> bool {
+    match comparison {
+        ComparisonOperator::GreaterThanThreshold => value > threshold,
+        ComparisonOperator::GreaterThanOrEqualToThreshold => value >= threshold,
+        ComparisonOperator::LessThanThreshold => value < threshold,
+        ComparisonOperator::LessThanOrEqualToThreshold => value <= threshold,
+    }
+}
+
+// Turns an AutoScalingConfig's metrics into capacity recommendations, e.g. queue depth or
+// consumer lag published by data-services for a worker instance group or ML deployment.
+// Thresholds are compared against an EWMA of each metric rather than the raw sample, and a
+// recommended change starts the config's cooldown.
+pub struct ScalingSignalAdapter {
+    metrics: Arc<dyn MetricsManager>,
+    smoothing: f64,
+    signals: Mutex<HashMap<(String, usize), SignalState>>,
+    last_change: Mutex<HashMap<String, DateTime<Utc>>>,
+}
+
+impl ScalingSignalAdapter {
+    pub fn new(metrics: Arc<dyn MetricsManager>) -> Self {
+        Self {
+            metrics,
+            smoothing: DEFAULT_SMOOTHING,
+            signals: Mutex::new(HashMap::new()),
+            last_change: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn with_smoothing(mut self, smoothing: f64) -> Self {
+        self.smoothing = smoothing.clamp(f64::EPSILON, 1.0);
+        self
+    }
+
+    async fn sample(&self, metric: &ScalingMetric, now: DateTime<Utc>) -> ComputeResult<Option<f64>> {
+        let query = MetricQuery {
+            metric_name: metric.name.clone(),
+            namespace: metric.namespace.clone(),
+            dimensions: (!metric.dimensions.is_empty()).then(|| metric.dimensions.clone()),
+            aggregation: aggregation(&metric.statistic),
+            period: metric.period_seconds,
+            start_time: now - chrono::Duration::seconds(i64::from(metric.period_seconds)),
+            end_time: now,
+            include_exemplars: false,
+        };
+        let points = self
+            .metrics
+            .get_metric_data(query)
+            .await
+            .map_err(|e| ComputeError::from_kind(e.kind(), e.to_string()))?;
+        Ok(reduce(&metric.statistic, points.iter().flat_map(values).collect()))
+    }
+
+    // Folds a new sample into the metric's EWMA and breach window
+    fn observe(&self, config_id: &str, index: usize, metric: &ScalingMetric, raw: f64) -> MetricSignal {
+        let mut signals = self.signals.lock().expect("scaling signal lock poisoned");
+        let state = signals.entry((config_id.to_string(), index)).or_default();
+        let smoothed = match state.smoothed {
+            Some(previous) => self.smoothing * raw + (1.0 - self.smoothing) * previous,
+            None => raw,
+        };
+        state.smoothed = Some(smoothed);
+        let breaching = breaches(&metric.comparison, smoothed, metric.threshold);
+        state.breaches.push_back(breaching);
+        while state.breaches.len() > metric.evaluation_periods.max(1) as usize {
+            state.breaches.pop_front();
+        }
+        let alarm = state.breaches.iter().filter(|b| **b).count() >= metric.datapoints_to_alarm.max(1) as usize;
+        MetricSignal {
+            metric: metric.name.clone(),
+            namespace: metric.namespace.clone(),
+            raw,
+            smoothed,
+            breaching,
+            alarm,
+        }
+    }
+
+    // Scale-out alarms win over scale-in ones; the largest adjustment in either direction is used
+    pub async fn evaluate(&self, config: &AutoScalingConfig, now: DateTime<Utc>) -> ComputeResult<ScalingRecommendation> {
+        if !config.is_valid() {
+            return Err(ComputeError::Validation(format!("Auto scaling config {} is invalid", config.id)));
+        }
+        let mut signals = Vec::with_capacity(config.metrics.len());
+        let mut adjustments = Vec::new();
+        for (index, metric) in config.metrics.iter().enumerate() {
+            let Some(raw) = self.sample(metric, now).await? else {
+                debug!("No {} datapoints for {} in the last period", metric.name, config.id);
+                continue;
+            };
+            let signal = self.observe(&config.id, index, metric, raw);
+            if signal.alarm {
+                adjustments.push((metric.scale_adjustment, metric.name.clone()));
+            }
+            signals.push(signal);
+        }
+
+        let current = config.desired_capacity;
+        let chosen = adjustments
+            .iter()
+            .filter(|(a, _)| *a > 0)
+            .max_by_key(|(a, _)| *a)
+            .or_else(|| adjustments.iter().filter(|(a, _)| *a < 0).min_by_key(|(a, _)| *a));
+        let (mut desired, mut reason) = match chosen {
+            Some((adjustment, name)) => (
+                (current + adjustment).clamp(config.min_capacity, config.max_capacity),
+                format!("{} alarm adjusts capacity by {}", name, adjustment),
+            ),
+            None => (current, "no metric in alarm".to_string()),
+        };
+        if desired == current && chosen.is_some() {
+            reason = format!("{}; already at capacity bound", reason);
+        }
+
+        if desired != current {
+            let mut last_change = self.last_change.lock().expect("scaling signal lock poisoned");
+            let cooldown = chrono::Duration::seconds(i64::from(config.cooldown_seconds));
+            match last_change.get(&config.id) {
+                Some(at) if now - *at < cooldown => {
+                    reason = format!("{}; held by cooldown until {}", reason, *at + cooldown);
+                    desired = current;
+                }
+                _ => {
+                    last_change.insert(config.id.clone(), now);
+                    info!("Recommending {} -> {} for {}: {}", current, desired, config.resource_id, reason);
+                }
+            }
+        }
+
+        Ok(ScalingRecommendation {
+            config_id: config.id.clone(),
+            resource_id: config.resource_id.clone(),
+            resource_type: config.resource_type.clone(),
+            current_capacity: current,
+            desired_capacity: desired,
+            signals,
+            reason,
+            evaluated_at: now,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use sirsi_observability::monitoring::MetricDefinition;
+    use sirsi_observability::ObservabilityResult;
+
+    // Serves whatever backlog the test set last, as one queue-scoped series
+    #[derive(Default)]
+    struct Backlog {
+        value: Mutex<f64>,
+    }
+
+    impl Backlog {
+        fn set(&self, value: f64) {
+            *self.value.lock().unwrap() = value;
+        }
+    }
+
+    #[async_trait]
+    impl MetricsManager for Backlog {
+        async fn register_metric(&self, _definition: MetricDefinition) -> ObservabilityResult<()> {
+            Ok(())
+        }
+
+        async fn put_metric_data(&self, _data_points: Vec<MetricDataPoint>) -> ObservabilityResult<()> {
+            Ok(())
+        }
+
+        async fn get_metric_data(&self, query: MetricQuery) -> ObservabilityResult<Vec<MetricDataPoint>> {
+            assert_eq!(query.dimensions.as_ref().unwrap()["QueueId"], "jobs");
+            Ok(vec![MetricDataPoint {
+                name: query.metric_name,
+                namespace: query.namespace,
+                dimensions: query.dimensions.unwrap_or_default(),
+                timestamp: query.end_time,
+                value: MetricValue::Single(*self.value.lock().unwrap()),
+                exemplars: Vec::new(),
+            }])
+        }
+
+        async fn list_metrics(&self, _namespace: Option<String>) -> ObservabilityResult<Vec<MetricDefinition>> {
+            Ok(Vec::new())
+        }
+
+        async fn delete_metric(&self, _name: &str, _namespace: &str) -> ObservabilityResult<()> {
+            Ok(())
+        }
+    }
+
+    fn lag_metric(comparison: ComparisonOperator, threshold: f64, periods: i32, adjustment: i32) -> ScalingMetric {
+        let mut metric = ScalingMetric::new("ConsumerLag".into(), "Sirsi/Queues".into())
+            .with_statistic(MetricStatistic::Maximum)
+            .with_comparison(comparison, threshold)
+            .with_evaluation(60, periods, periods)
+            .with_scale_adjustment(adjustment);
+        metric.add_dimension("QueueId".into(), "jobs".into());
+        metric
+    }
+
+    fn workers(cooldown_seconds: i32, out_threshold: f64, in_threshold: f64) -> AutoScalingConfig {
+        let mut config = AutoScalingConfig::new("asg-workers".into(), "workers".into(), "ig-workers".into(), ResourceType::InstanceGroup)
+            .with_capacity(1, 10, 2);
+        config.cooldown_seconds = cooldown_seconds;
+        config.add_metric(lag_metric(ComparisonOperator::GreaterThanThreshold, out_threshold, 2, 2));
+        config.add_metric(lag_metric(ComparisonOperator::LessThanThreshold, in_threshold, 3, -1));
+        config
+    }
+
+    #[tokio::test]
+    async fn test_backlog_curve_scales_out_then_in() {
+        let backlog = Arc::new(Backlog::default());
+        let adapter = ScalingSignalAdapter::new(backlog.clone());
+        let mut config = workers(0, 500.0, 50.0);
+        let start = Utc::now();
+
+        // Ramp to 2000, hold, then drain
+        let curve: Vec<f64> = (0..=10)
+            .map(|i| i as f64 * 200.0)
+            .chain(std::iter::repeat_n(2000.0, 5))
+            .chain((0..=10).rev().map(|i| i as f64 * 200.0))
+            .chain(std::iter::repeat_n(0.0, 24))
+            .collect();
+        let mut history = Vec::new();
+        for (tick, raw) in curve.iter().enumerate() {
+            backlog.set(*raw);
+            let recommendation = adapter
+                .evaluate(&config, start + chrono::Duration::minutes(tick as i64))
+                .await
+                .unwrap();
+            assert_eq!(recommendation.signals[0].raw, *raw);
+            config.desired_capacity = recommendation.desired_capacity;
+            history.push(recommendation);
+        }
+
+        // Smoothing delays the first scale-out past the tick where raw lag crossed the threshold
+        let raw_crossing = curve.iter().position(|v| *v > 500.0).unwrap();
+        let first_out = history.iter().position(|r| r.desired_capacity > r.current_capacity).unwrap();
+        assert!(first_out > raw_crossing);
+        assert!(history[first_out].signals[0].smoothed < history[first_out].signals[0].raw);
+        assert_eq!(history.iter().map(|r| r.desired_capacity).max(), Some(10));
+        assert!(history.iter().all(|r| (1..=10).contains(&r.desired_capacity)));
+
+        // Scale-in only starts once the smoothed lag has stayed low for three periods
+        let first_in = history.iter().position(|r| r.desired_capacity < r.current_capacity).unwrap();
+        assert!(curve[first_in - 3..=first_in].iter().all(|v| *v < 50.0));
+        assert_eq!(config.desired_capacity, 1);
+    }
+
+    #[tokio::test]
+    async fn test_smoothing_and_cooldown_prevent_flapping() {
+        let backlog = Arc::new(Backlog::default());
+        let adapter = ScalingSignalAdapter::new(backlog.clone()).with_smoothing(0.2);
+        let mut config = workers(600, 650.0, 100.0);
+        for metric in &mut config.metrics {
+            metric.evaluation_periods = 1;
+            metric.datapoints_to_alarm = 1;
+        }
+        let start = Utc::now();
+
+        // Raw lag swings across both thresholds every period; its EWMA stays between them
+        for (tick, raw) in std::iter::once(500.0).chain([1000.0, 0.0].repeat(10)).enumerate() {
+            backlog.set(raw);
+            let recommendation = adapter
+                .evaluate(&config, start + chrono::Duration::minutes(tick as i64))
+                .await
+                .unwrap();
+            let signal = &recommendation.signals[0];
+            assert!(signal.smoothed > 100.0 && signal.smoothed < 650.0, "smoothed {}", signal.smoothed);
+            assert_eq!(recommendation.desired_capacity, 2);
+        }
+
+        // A sustained surge scales once, then the cooldown holds further changes
+        let later = start + chrono::Duration::hours(1);
+        let mut desired = Vec::new();
+        for tick in 0..5 {
+            backlog.set(5000.0);
+            let recommendation = adapter.evaluate(&config, later + chrono::Duration::minutes(tick)).await.unwrap();
+            config.desired_capacity = recommendation.desired_capacity;
+            desired.push(recommendation.desired_capacity);
+        }
+        assert_eq!(desired, vec![4, 4, 4, 4, 4]);
+    }
+}
@@ -0,0 +1,575 @@
+// Read-through cache for provider inventory. Listings are served from memory while fresh, served
+// stale and refreshed in the background for a grace period after that, and dropped whenever one
+// of our own mutations would change them.
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sirsi_observability::monitoring::{
+    AggregationType, MetricDataPoint, MetricDefinition, MetricType, MetricUnit, MetricValue,
+};
+use tokio::time::Instant;
+use tracing::{debug, warn};
+
+use crate::error::ComputeResult;
+use crate::governor::{DIM_ACCOUNT, METRICS_NAMESPACE};
+
+pub mod provider;
+
+pub use provider::CachedProvider;
+
+pub const HIT_RATE_METRIC: &str = "inventory_cache_hit_rate";
+pub const HITS_METRIC: &str = "inventory_cache_hits";
+pub const MISSES_METRIC: &str = "inventory_cache_misses";
+pub const STALE_SERVED_METRIC: &str = "inventory_cache_stale_served";
+pub const REFRESH_FAILURES_METRIC: &str = "inventory_cache_refresh_failures";
+
+pub const DIM_OPERATION: &str = "operation";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CachedOperation {
+    ListFleets,
+    GetFleet,
+    ListInstanceGroups,
+    GetInstanceGroup,
+    ListInstances,
+    GetInstance,
+}
+
+impl CachedOperation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CachedOperation::ListFleets => "list_fleets",
+            CachedOperation::GetFleet => "get_fleet",
+            CachedOperation::ListInstanceGroups => "list_instance_groups",
+            CachedOperation::GetInstanceGroup => "get_instance_group",
+            CachedOperation::ListInstances => "list_instances",
+            CachedOperation::GetInstance => "get_instance",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheTtl {
+    // Served without going to the provider
+    pub fresh_for: Duration,
+    // After `fresh_for`, still served while a background refresh runs; past this the caller waits
+    pub stale_for: Duration,
+}
+
+impl CacheTtl {
+    pub fn new(fresh_for: Duration, stale_for: Duration) -> Self {
+        Self { fresh_for, stale_for }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CacheConfig {
+    ttls: HashMap<CachedOperation, CacheTtl>,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        let minutes = |m: u64| Duration::from_secs(m * 60);
+        Self {
+            ttls: HashMap::from([
+                (CachedOperation::ListFleets, CacheTtl::new(minutes(1), minutes(10))),
+                (CachedOperation::GetFleet, CacheTtl::new(minutes(1), minutes(10))),
+                (CachedOperation::ListInstanceGroups, CacheTtl::new(minutes(1), minutes(10))),
+                (CachedOperation::GetInstanceGroup, CacheTtl::new(minutes(1), minutes(10))),
+                // Instance state changes on its own, so instance reads go stale sooner
+                (CachedOperation::ListInstances, CacheTtl::new(Duration::from_secs(30), minutes(5))),
+                (CachedOperation::GetInstance, CacheTtl::new(Duration::from_secs(15), minutes(2))),
+            ]),
+        }
+    }
+}
+
+impl CacheConfig {
+    pub fn with_ttl(mut self, operation: CachedOperation, ttl: CacheTtl) -> Self {
+        self.ttls.insert(operation, ttl);
+        self
+    }
+
+    pub fn ttl(&self, operation: CachedOperation) -> CacheTtl {
+        self.ttls.get(&operation).copied().unwrap_or(CacheTtl::new(Duration::ZERO, Duration::ZERO))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    pub account: String,
+    pub operation: CachedOperation,
+    pub params: Vec<String>,
+}
+
+impl CacheKey {
+    pub fn new(account: impl Into<String>, operation: CachedOperation, params: &[&str]) -> Self {
+        Self {
+            account: account.into(),
+            operation,
+            params: params.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+
+    fn matches(&self, account: &str, operation: CachedOperation, prefix: &[&str]) -> bool {
+        self.account == account
+            && self.operation == operation
+            && prefix.len() <= self.params.len()
+            && prefix.iter().zip(&self.params).all(|(p, q)| p == q)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReadOptions {
+    // Skip the cache and wait for the provider; the result replaces the cached entry
+    pub force_refresh: bool,
+}
+
+impl ReadOptions {
+    pub fn force_refresh() -> Self {
+        Self { force_refresh: true }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CacheSource {
+    Provider,
+    Cache,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Freshness {
+    pub source: CacheSource,
+    pub fetched_at: DateTime<Utc>,
+    pub age_seconds: f64,
+    // Past its fresh TTL; a newer copy is being fetched in the background
+    pub stale: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Cached<T> {
+    pub value: T,
+    pub freshness: Freshness,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub stale_served: u64,
+    pub refresh_failures: u64,
+}
+
+impl CacheStats {
+    pub fn hit_rate(&self) -> f64 {
+        let reads = self.hits + self.misses;
+        if reads == 0 {
+            0.0
+        } else {
+            self.hits as f64 / reads as f64
+        }
+    }
+
+    fn since(&self, earlier: &CacheStats) -> CacheStats {
+        CacheStats {
+            hits: self.hits - earlier.hits,
+            misses: self.misses - earlier.misses,
+            stale_served: self.stale_served - earlier.stale_served,
+            refresh_failures: self.refresh_failures - earlier.refresh_failures,
+        }
+    }
+}
+
+struct Entry {
+    value: Arc<dyn Any + Send + Sync>,
+    fetched: Instant,
+    fetched_at: DateTime<Utc>,
+    refreshing: bool,
+}
+
+#[derive(Default)]
+struct State {
+    entries: HashMap<CacheKey, Entry>,
+    // Bumped by every invalidation. A fetch that started before one doesn't store its result,
+    // since it may have read the provider before our mutation landed.
+    epoch: u64,
+    stats: HashMap<(String, CachedOperation), CacheStats>,
+    reported: HashMap<(String, CachedOperation), CacheStats>,
+}
+
+impl State {
+    fn stats(&mut self, key: &CacheKey) -> &mut CacheStats {
+        self.stats.entry((key.account.clone(), key.operation)).or_default()
+    }
+
+    fn store(&mut self, key: CacheKey, value: Arc<dyn Any + Send + Sync>, fetched_at: DateTime<Utc>, epoch: u64) {
+        if epoch == self.epoch {
+            self.entries.insert(key, Entry { value, fetched: Instant::now(), fetched_at, refreshing: false });
+        } else if let Some(entry) = self.entries.get_mut(&key) {
+            entry.refreshing = false;
+        }
+    }
+}
+
+enum Lookup<T> {
+    Fresh(Cached<T>),
+    // Serve this; the caller that saw it first starts the refresh
+    Stale(Cached<T>, bool),
+    Miss(u64),
+}
+
+pub struct InventoryCache {
+    config: CacheConfig,
+    state: Mutex<State>,
+}
+
+impl InventoryCache {
+    pub fn new(config: CacheConfig) -> Self {
+        Self { config, state: Mutex::new(State::default()) }
+    }
+
+    fn lookup<T: Clone + Send + Sync + 'static>(&self, key: &CacheKey, options: ReadOptions) -> Lookup<T> {
+        let ttl = self.config.ttl(key.operation);
+        let mut state = self.state.lock().unwrap();
+        let epoch = state.epoch;
+        let found = if options.force_refresh {
+            None
+        } else {
+            state.entries.get_mut(key).and_then(|entry| {
+                let value = entry.value.downcast_ref::<T>()?.clone();
+                let age = entry.fetched.elapsed();
+                if age > ttl.fresh_for + ttl.stale_for {
+                    return None;
+                }
+                let stale = age > ttl.fresh_for;
+                let start_refresh = stale && !entry.refreshing;
+                if start_refresh {
+                    entry.refreshing = true;
+                }
+                let freshness = Freshness {
+                    source: CacheSource::Cache,
+                    fetched_at: entry.fetched_at,
+                    age_seconds: age.as_secs_f64(),
+                    stale,
+                };
+                Some((Cached { value, freshness }, start_refresh))
+            })
+        };
+        let stats = state.stats(key);
+        match found {
+            Some((cached, start_refresh)) if cached.freshness.stale => {
+                stats.hits += 1;
+                stats.stale_served += 1;
+                Lookup::Stale(cached, start_refresh)
+            }
+            Some((cached, _)) => {
+                stats.hits += 1;
+                Lookup::Fresh(cached)
+            }
+            None => {
+                stats.misses += 1;
+                Lookup::Miss(epoch)
+            }
+        }
+    }
+
+    pub async fn get<T, F, Fut>(self: &Arc<Self>, key: CacheKey, options: ReadOptions, fetch: F) -> ComputeResult<Cached<T>>
+    where
+        T: Clone + Send + Sync + 'static,
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = ComputeResult<T>> + Send + 'static,
+    {
+        match self.lookup::<T>(&key, options) {
+            Lookup::Fresh(cached) => Ok(cached),
+            Lookup::Stale(cached, start_refresh) => {
+                if start_refresh {
+                    self.spawn_refresh(key, fetch);
+                }
+                Ok(cached)
+            }
+            Lookup::Miss(epoch) => {
+                let value = fetch().await?;
+                let fetched_at = Utc::now();
+                self.state.lock().unwrap().store(key, Arc::new(value.clone()), fetched_at, epoch);
+                Ok(Cached {
+                    value,
+                    freshness: Freshness { source: CacheSource::Provider, fetched_at, age_seconds: 0.0, stale: false },
+                })
+            }
+        }
+    }
+
+    fn spawn_refresh<T, F, Fut>(self: &Arc<Self>, key: CacheKey, fetch: F) -> tokio::task::JoinHandle<()>
+    where
+        T: Clone + Send + Sync + 'static,
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = ComputeResult<T>> + Send + 'static,
+    {
+        let cache = self.clone();
+        let epoch = self.state.lock().unwrap().epoch;
+        tokio::spawn(async move {
+            let result = fetch().await;
+            let mut state = cache.state.lock().unwrap();
+            match result {
+                Ok(value) => {
+                    debug!("Refreshed {} for {}", key.operation.as_str(), key.account);
+                    state.store(key, Arc::new(value), Utc::now(), epoch);
+                }
+                Err(e) => {
+                    // Keep serving the old copy until it ages out
+                    warn!("Background refresh of {} for {} failed: {}", key.operation.as_str(), key.account, e);
+                    state.stats(&key).refresh_failures += 1;
+                    if let Some(entry) = state.entries.get_mut(&key) {
+                        entry.refreshing = false;
+                    }
+                }
+            }
+        })
+    }
+
+    // Drops every entry for `operation` whose parameters start with `prefix`
+    pub fn invalidate(&self, account: &str, operation: CachedOperation, prefix: &[&str]) {
+        let mut state = self.state.lock().unwrap();
+        state.epoch += 1;
+        state.entries.retain(|key, _| !key.matches(account, operation, prefix));
+    }
+
+    pub fn invalidate_account(&self, account: &str) {
+        let mut state = self.state.lock().unwrap();
+        state.epoch += 1;
+        state.entries.retain(|key, _| key.account != account);
+    }
+
+    pub fn stats(&self, account: &str, operation: CachedOperation) -> CacheStats {
+        self.state.lock().unwrap().stats.get(&(account.to_string(), operation)).cloned().unwrap_or_default()
+    }
+
+    // Counts and hit rate cover the reads since the last call
+    pub fn metric_points(&self, now: DateTime<Utc>) -> Vec<MetricDataPoint> {
+        let mut state = self.state.lock().unwrap();
+        let state = &mut *state;
+        let mut points = Vec::new();
+        for ((account, operation), stats) in &state.stats {
+            let reported = state.reported.entry((account.clone(), *operation)).or_default();
+            let delta = stats.since(reported);
+            *reported = stats.clone();
+
+            let dimensions = HashMap::from([
+                (DIM_ACCOUNT.to_string(), account.clone()),
+                (DIM_OPERATION.to_string(), operation.as_str().to_string()),
+            ]);
+            for (name, value) in [
+                (HIT_RATE_METRIC, delta.hit_rate() * 100.0),
+                (HITS_METRIC, delta.hits as f64),
+                (MISSES_METRIC, delta.misses as f64),
+                (STALE_SERVED_METRIC, delta.stale_served as f64),
+                (REFRESH_FAILURES_METRIC, delta.refresh_failures as f64),
+            ] {
+                points.push(MetricDataPoint {
+                    name: name.to_string(),
+                    namespace: METRICS_NAMESPACE.to_string(),
+                    dimensions: dimensions.clone(),
+                    timestamp: now,
+                    value: MetricValue::Single(value),
+                    exemplars: Vec::new(),
+                });
+            }
+        }
+        points
+    }
+}
+
+pub fn metric_definitions() -> Vec<MetricDefinition> {
+    let definition = |name: &str, metric_type: MetricType, unit: MetricUnit, aggregations: Vec<AggregationType>| MetricDefinition {
+        name: name.to_string(),
+        namespace: METRICS_NAMESPACE.to_string(),
+        metric_type,
+        unit,
+        dimensions: vec![DIM_ACCOUNT.to_string(), DIM_OPERATION.to_string()],
+        aggregations,
+        retention_days: 30,
+    };
+    let counter = |name: &str| definition(name, MetricType::Counter, MetricUnit::Count, vec![AggregationType::Sum]);
+    vec![
+        definition(HIT_RATE_METRIC, MetricType::Gauge, MetricUnit::Percent, vec![AggregationType::Average, AggregationType::Minimum]),
+        counter(HITS_METRIC),
+        counter(MISSES_METRIC),
+        counter(STALE_SERVED_METRIC),
+        counter(REFRESH_FAILURES_METRIC),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::autoscaling::AutoScalingConfig;
+    use crate::error::ComputeError;
+    use crate::fleet::{FleetConfig, Instance, InstanceGroup, InstanceState};
+    use crate::optimization::OptimizationStrategy;
+    use crate::provider::{Provider, ProviderConfig};
+    use crate::serverless::{Function, FunctionConfig};
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+    // Counts listing calls; each instance listing reports the call number as the instance type
+    #[derive(Default)]
+    struct CountingProvider {
+        fleets: Mutex<Vec<FleetConfig>>,
+        fleet_lists: AtomicUsize,
+        instance_lists: AtomicUsize,
+        list_delay_ms: AtomicU64,
+    }
+
+    fn unsupported<T>() -> ComputeResult<T> {
+        Err(ComputeError::Config("not supported by the counting provider".into()))
+    }
+
+    #[async_trait]
+    impl Provider for CountingProvider {
+        async fn init(_config: ProviderConfig) -> ComputeResult<Box<dyn Provider>> {
+            Ok(Box::new(CountingProvider::default()))
+        }
+
+        async fn create_fleet(&self, config: FleetConfig) -> ComputeResult<FleetConfig> {
+            self.fleets.lock().unwrap().push(config.clone());
+            Ok(config)
+        }
+        async fn update_fleet(&self, _: FleetConfig) -> ComputeResult<FleetConfig> { unsupported() }
+        async fn delete_fleet(&self, fleet_id: &str) -> ComputeResult<()> {
+            self.fleets.lock().unwrap().retain(|fleet| fleet.id != fleet_id);
+            Ok(())
+        }
+        async fn get_fleet(&self, _: &str) -> ComputeResult<FleetConfig> { unsupported() }
+        async fn list_fleets(&self) -> ComputeResult<Vec<FleetConfig>> {
+            self.fleet_lists.fetch_add(1, Ordering::SeqCst);
+            Ok(self.fleets.lock().unwrap().clone())
+        }
+
+        async fn create_instance_group(&self, _: &str, _: InstanceGroup) -> ComputeResult<InstanceGroup> { unsupported() }
+        async fn update_instance_group(&self, _: &str, _: InstanceGroup) -> ComputeResult<InstanceGroup> { unsupported() }
+        async fn delete_instance_group(&self, _: &str, _: &str) -> ComputeResult<()> { unsupported() }
+        async fn get_instance_group(&self, _: &str, _: &str) -> ComputeResult<InstanceGroup> { unsupported() }
+        async fn list_instance_groups(&self, _: &str) -> ComputeResult<Vec<InstanceGroup>> { unsupported() }
+
+        async fn start_instance(&self, _: &str) -> ComputeResult<()> { unsupported() }
+        async fn stop_instance(&self, _: &str) -> ComputeResult<()> { unsupported() }
+        async fn restart_instance(&self, _: &str) -> ComputeResult<()> { unsupported() }
+        async fn terminate_instance(&self, _: &str) -> ComputeResult<()> { unsupported() }
+        async fn get_instance(&self, _: &str) -> ComputeResult<Instance> { unsupported() }
+        async fn list_instances(&self, fleet_id: &str, group_id: Option<&str>) -> ComputeResult<Vec<Instance>> {
+            let call = self.instance_lists.fetch_add(1, Ordering::SeqCst) + 1;
+            tokio::time::sleep(Duration::from_millis(self.list_delay_ms.load(Ordering::SeqCst))).await;
+            Ok(vec![Instance {
+                instance_id: "i-1".into(),
+                group_id: group_id.unwrap_or("web").into(),
+                fleet_id: fleet_id.into(),
+                instance_type: format!("call-{}", call),
+                private_ip: "10.0.0.4".into(),
+                public_ip: None,
+                state: InstanceState::Running,
+                launch_time: Utc::now(),
+                labels: HashMap::new(),
+                metrics: None,
+            }])
+        }
+
+        async fn create_function(&self, _: FunctionConfig) -> ComputeResult<Function> { unsupported() }
+        async fn update_function(&self, _: FunctionConfig) -> ComputeResult<Function> { unsupported() }
+        async fn delete_function(&self, _: &str) -> ComputeResult<()> { unsupported() }
+        async fn get_function(&self, _: &str) -> ComputeResult<Function> { unsupported() }
+        async fn list_functions(&self) -> ComputeResult<Vec<Function>> { unsupported() }
+        async fn invoke_function(&self, _: &str, _: Vec<u8>) -> ComputeResult<Vec<u8>> { unsupported() }
+
+        async fn configure_auto_scaling(&self, _: AutoScalingConfig) -> ComputeResult<AutoScalingConfig> { unsupported() }
+        async fn update_auto_scaling(&self, _: AutoScalingConfig) -> ComputeResult<AutoScalingConfig> { unsupported() }
+        async fn delete_auto_scaling(&self, _: &str) -> ComputeResult<()> { unsupported() }
+        async fn get_auto_scaling(&self, _: &str) -> ComputeResult<AutoScalingConfig> { unsupported() }
+        async fn list_auto_scaling(&self) -> ComputeResult<Vec<AutoScalingConfig>> { unsupported() }
+
+        async fn analyze_resources(&self, _: Vec<String>) -> ComputeResult<Vec<OptimizationStrategy>> { unsupported() }
+        async fn apply_optimization(&self, _: OptimizationStrategy) -> ComputeResult<()> { unsupported() }
+        async fn get_optimization_history(&self, _: &str) -> ComputeResult<Vec<OptimizationStrategy>> { unsupported() }
+        async fn get_metrics(&self, _: &str, _: Vec<String>) -> ComputeResult<Vec<(String, f64)>> { unsupported() }
+        async fn get_logs(&self, _: &str, _: i64, _: i64) -> ComputeResult<Vec<String>> { unsupported() }
+    }
+
+    fn cached(inner: Arc<CountingProvider>, config: CacheConfig) -> (CachedProvider, Arc<InventoryCache>) {
+        let cache = Arc::new(InventoryCache::new(config));
+        (CachedProvider::new(inner, cache.clone(), "aws:123456789012"), cache)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_mutations_invalidate_listings() {
+        let inner = Arc::new(CountingProvider::default());
+        let (provider, cache) = cached(inner.clone(), CacheConfig::default());
+
+        assert!(provider.list_fleets().await.unwrap().is_empty());
+        assert!(provider.list_fleets().await.unwrap().is_empty());
+        assert_eq!(inner.fleet_lists.load(Ordering::SeqCst), 1);
+
+        provider.create_fleet(FleetConfig::new("fleet-1".into(), "web".into())).await.unwrap();
+        let listed = provider.list_fleets_with(ReadOptions::default()).await.unwrap();
+        assert_eq!(listed.value.len(), 1);
+        assert_eq!(listed.freshness.source, CacheSource::Provider);
+        assert_eq!(inner.fleet_lists.load(Ordering::SeqCst), 2);
+
+        let forced = provider.list_fleets_with(ReadOptions::force_refresh()).await.unwrap();
+        assert_eq!(forced.freshness.source, CacheSource::Provider);
+        assert_eq!(inner.fleet_lists.load(Ordering::SeqCst), 3);
+
+        provider.delete_fleet("fleet-1").await.unwrap();
+        assert!(provider.list_fleets().await.unwrap().is_empty());
+        assert_eq!(inner.fleet_lists.load(Ordering::SeqCst), 4);
+
+        let stats = cache.stats("aws:123456789012", CachedOperation::ListFleets);
+        assert_eq!((stats.hits, stats.misses), (1, 4));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_stale_listing_served_during_slow_refresh() {
+        let inner = Arc::new(CountingProvider::default());
+        let ttl = CacheTtl::new(Duration::from_secs(30), Duration::from_secs(300));
+        let (provider, cache) = cached(inner.clone(), CacheConfig::default().with_ttl(CachedOperation::ListInstances, ttl));
+
+        let first = provider.list_instances_with("fleet-1", None, ReadOptions::default()).await.unwrap();
+        assert_eq!(first.value[0].instance_type, "call-1");
+
+        tokio::time::advance(Duration::from_secs(45)).await;
+        inner.list_delay_ms.store(5_000, Ordering::SeqCst);
+
+        // Both reads answer from the old copy without waiting, and only one refresh is started
+        let started = Instant::now();
+        let stale = provider.list_instances_with("fleet-1", None, ReadOptions::default()).await.unwrap();
+        let again = provider.list_instances_with("fleet-1", None, ReadOptions::default()).await.unwrap();
+        assert_eq!(started.elapsed(), Duration::ZERO);
+        assert_eq!(stale.value[0].instance_type, "call-1");
+        assert_eq!(again.value[0].instance_type, "call-1");
+        assert_eq!(stale.freshness.source, CacheSource::Cache);
+        assert!(stale.freshness.stale);
+        assert_eq!(stale.freshness.fetched_at, first.freshness.fetched_at);
+        assert!((stale.freshness.age_seconds - 45.0).abs() < 0.01);
+
+        tokio::time::sleep(Duration::from_secs(6)).await;
+        let refreshed = provider.list_instances_with("fleet-1", None, ReadOptions::default()).await.unwrap();
+        assert_eq!(refreshed.value[0].instance_type, "call-2");
+        assert!(!refreshed.freshness.stale);
+        assert!(refreshed.freshness.age_seconds < 2.0);
+        assert_eq!(inner.instance_lists.load(Ordering::SeqCst), 2);
+
+        let points = cache.metric_points(Utc::now());
+        let value = |name: &str| match points.iter().find(|p| p.name == name).unwrap().value {
+            MetricValue::Single(v) => v,
+            _ => unreachable!(),
+        };
+        assert_eq!(value(STALE_SERVED_METRIC), 2.0);
+        assert_eq!(value(HIT_RATE_METRIC), 75.0);
+    }
+}
@@ -0,0 +1,232 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::autoscaling::AutoScalingConfig;
+use crate::error::{ComputeError, ComputeResult};
+use crate::fleet::{FleetConfig, Instance, InstanceGroup};
+use crate::optimization::OptimizationStrategy;
+use crate::provider::{Provider, ProviderConfig};
+use crate::serverless::{Function, FunctionConfig};
+use super::{CacheKey, Cached, CachedOperation, InventoryCache, ReadOptions};
+
+const ALL_GROUPS: &str = "*";
+
+// Caches one provider account's inventory reads. Mutations made through this wrapper drop the
+// listings they affect; `*_with` variants take `ReadOptions` and report how fresh the data is.
+#[derive(Clone)]
+pub struct CachedProvider {
+    inner: Arc<dyn Provider>,
+    cache: Arc<InventoryCache>,
+    account: String,
+}
+
+impl CachedProvider {
+    pub fn new(inner: Arc<dyn Provider>, cache: Arc<InventoryCache>, account: impl Into<String>) -> Self {
+        Self { inner, cache, account: account.into() }
+    }
+
+    fn key(&self, operation: CachedOperation, params: &[&str]) -> CacheKey {
+        CacheKey::new(self.account.clone(), operation, params)
+    }
+
+    pub async fn list_fleets_with(&self, options: ReadOptions) -> ComputeResult<Cached<Vec<FleetConfig>>> {
+        let inner = self.inner.clone();
+        self.cache
+            .get(self.key(CachedOperation::ListFleets, &[]), options, move || async move { inner.list_fleets().await })
+            .await
+    }
+
+    pub async fn get_fleet_with(&self, fleet_id: &str, options: ReadOptions) -> ComputeResult<Cached<FleetConfig>> {
+        let (inner, id) = (self.inner.clone(), fleet_id.to_string());
+        self.cache
+            .get(self.key(CachedOperation::GetFleet, &[fleet_id]), options, move || async move { inner.get_fleet(&id).await })
+            .await
+    }
+
+    pub async fn list_instance_groups_with(&self, fleet_id: &str, options: ReadOptions) -> ComputeResult<Cached<Vec<InstanceGroup>>> {
+        let (inner, id) = (self.inner.clone(), fleet_id.to_string());
+        let key = self.key(CachedOperation::ListInstanceGroups, &[fleet_id]);
+        self.cache.get(key, options, move || async move { inner.list_instance_groups(&id).await }).await
+    }
+
+    pub async fn get_instance_group_with(
+        &self,
+        fleet_id: &str,
+        group_id: &str,
+        options: ReadOptions,
+    ) -> ComputeResult<Cached<InstanceGroup>> {
+        let (inner, fleet, group) = (self.inner.clone(), fleet_id.to_string(), group_id.to_string());
+        let key = self.key(CachedOperation::GetInstanceGroup, &[fleet_id, group_id]);
+        self.cache.get(key, options, move || async move { inner.get_instance_group(&fleet, &group).await }).await
+    }
+
+    pub async fn list_instances_with(
+        &self,
+        fleet_id: &str,
+        group_id: Option<&str>,
+        options: ReadOptions,
+    ) -> ComputeResult<Cached<Vec<Instance>>> {
+        let (inner, fleet, group) = (self.inner.clone(), fleet_id.to_string(), group_id.map(str::to_string));
+        let key = self.key(CachedOperation::ListInstances, &[fleet_id, group_id.unwrap_or(ALL_GROUPS)]);
+        self.cache.get(key, options, move || async move { inner.list_instances(&fleet, group.as_deref()).await }).await
+    }
+
+    pub async fn get_instance_with(&self, instance_id: &str, options: ReadOptions) -> ComputeResult<Cached<Instance>> {
+        let (inner, id) = (self.inner.clone(), instance_id.to_string());
+        let key = self.key(CachedOperation::GetInstance, &[instance_id]);
+        self.cache.get(key, options, move || async move { inner.get_instance(&id).await }).await
+    }
+
+    fn invalidate_fleet(&self, fleet_id: &str) {
+        let cache = &self.cache;
+        cache.invalidate(&self.account, CachedOperation::ListFleets, &[]);
+        cache.invalidate(&self.account, CachedOperation::GetFleet, &[fleet_id]);
+        cache.invalidate(&self.account, CachedOperation::ListInstanceGroups, &[fleet_id]);
+        cache.invalidate(&self.account, CachedOperation::GetInstanceGroup, &[fleet_id]);
+        cache.invalidate(&self.account, CachedOperation::ListInstances, &[fleet_id]);
+    }
+
+    // We only know the instance id here, so every instance listing for the account goes
+    fn invalidate_instance(&self, instance_id: &str) {
+        self.cache.invalidate(&self.account, CachedOperation::GetInstance, &[instance_id]);
+        self.cache.invalidate(&self.account, CachedOperation::ListInstances, &[]);
+    }
+}
+
+#[async_trait]
+impl Provider for CachedProvider {
+    async fn init(_config: ProviderConfig) -> ComputeResult<Box<dyn Provider>> {
+        Err(ComputeError::Config("CachedProvider wraps an initialized provider; use CachedProvider::new".into()))
+    }
+
+    async fn create_fleet(&self, config: FleetConfig) -> ComputeResult<FleetConfig> {
+        let fleet = self.inner.create_fleet(config).await?;
+        self.invalidate_fleet(&fleet.id);
+        Ok(fleet)
+    }
+    async fn update_fleet(&self, config: FleetConfig) -> ComputeResult<FleetConfig> {
+        let fleet = self.inner.update_fleet(config).await?;
+        self.invalidate_fleet(&fleet.id);
+        Ok(fleet)
+    }
+    async fn delete_fleet(&self, fleet_id: &str) -> ComputeResult<()> {
+        self.inner.delete_fleet(fleet_id).await?;
+        self.invalidate_fleet(fleet_id);
+        Ok(())
+    }
+    async fn get_fleet(&self, fleet_id: &str) -> ComputeResult<FleetConfig> {
+        Ok(self.get_fleet_with(fleet_id, ReadOptions::default()).await?.value)
+    }
+    async fn list_fleets(&self) -> ComputeResult<Vec<FleetConfig>> {
+        Ok(self.list_fleets_with(ReadOptions::default()).await?.value)
+    }
+
+    async fn create_instance_group(&self, fleet_id: &str, group: InstanceGroup) -> ComputeResult<InstanceGroup> {
+        let group = self.inner.create_instance_group(fleet_id, group).await?;
+        self.invalidate_fleet(fleet_id);
+        Ok(group)
+    }
+    async fn update_instance_group(&self, fleet_id: &str, group: InstanceGroup) -> ComputeResult<InstanceGroup> {
+        let group = self.inner.update_instance_group(fleet_id, group).await?;
+        self.invalidate_fleet(fleet_id);
+        Ok(group)
+    }
+    async fn delete_instance_group(&self, fleet_id: &str, group_id: &str) -> ComputeResult<()> {
+        self.inner.delete_instance_group(fleet_id, group_id).await?;
+        self.invalidate_fleet(fleet_id);
+        Ok(())
+    }
+    async fn get_instance_group(&self, fleet_id: &str, group_id: &str) -> ComputeResult<InstanceGroup> {
+        Ok(self.get_instance_group_with(fleet_id, group_id, ReadOptions::default()).await?.value)
+    }
+    async fn list_instance_groups(&self, fleet_id: &str) -> ComputeResult<Vec<InstanceGroup>> {
+        Ok(self.list_instance_groups_with(fleet_id, ReadOptions::default()).await?.value)
+    }
+
+    async fn start_instance(&self, instance_id: &str) -> ComputeResult<()> {
+        self.inner.start_instance(instance_id).await?;
+        self.invalidate_instance(instance_id);
+        Ok(())
+    }
+    async fn stop_instance(&self, instance_id: &str) -> ComputeResult<()> {
+        self.inner.stop_instance(instance_id).await?;
+        self.invalidate_instance(instance_id);
+        Ok(())
+    }
+    async fn restart_instance(&self, instance_id: &str) -> ComputeResult<()> {
+        self.inner.restart_instance(instance_id).await?;
+        self.invalidate_instance(instance_id);
+        Ok(())
+    }
+    async fn terminate_instance(&self, instance_id: &str) -> ComputeResult<()> {
+        self.inner.terminate_instance(instance_id).await?;
+        self.invalidate_instance(instance_id);
+        Ok(())
+    }
+    async fn get_instance(&self, instance_id: &str) -> ComputeResult<Instance> {
+        Ok(self.get_instance_with(instance_id, ReadOptions::default()).await?.value)
+    }
+    async fn list_instances(&self, fleet_id: &str, group_id: Option<&str>) -> ComputeResult<Vec<Instance>> {
+        Ok(self.list_instances_with(fleet_id, group_id, ReadOptions::default()).await?.value)
+    }
+
+    async fn create_function(&self, config: FunctionConfig) -> ComputeResult<Function> {
+        self.inner.create_function(config).await
+    }
+    async fn update_function(&self, config: FunctionConfig) -> ComputeResult<Function> {
+        self.inner.update_function(config).await
+    }
+    async fn delete_function(&self, function_id: &str) -> ComputeResult<()> {
+        self.inner.delete_function(function_id).await
+    }
+    async fn get_function(&self, function_id: &str) -> ComputeResult<Function> {
+        self.inner.get_function(function_id).await
+    }
+    async fn list_functions(&self) -> ComputeResult<Vec<Function>> {
+        self.inner.list_functions().await
+    }
+    async fn invoke_function(&self, function_id: &str, payload: Vec<u8>) -> ComputeResult<Vec<u8>> {
+        self.inner.invoke_function(function_id, payload).await
+    }
+
+    // Scaling policies resize groups, so the fleet's instance listings are dropped along with them
+    async fn configure_auto_scaling(&self, config: AutoScalingConfig) -> ComputeResult<AutoScalingConfig> {
+        let config = self.inner.configure_auto_scaling(config).await?;
+        self.cache.invalidate(&self.account, CachedOperation::ListInstances, &[]);
+        Ok(config)
+    }
+    async fn update_auto_scaling(&self, config: AutoScalingConfig) -> ComputeResult<AutoScalingConfig> {
+        let config = self.inner.update_auto_scaling(config).await?;
+        self.cache.invalidate(&self.account, CachedOperation::ListInstances, &[]);
+        Ok(config)
+    }
+    async fn delete_auto_scaling(&self, config_id: &str) -> ComputeResult<()> {
+        self.inner.delete_auto_scaling(config_id).await
+    }
+    async fn get_auto_scaling(&self, config_id: &str) -> ComputeResult<AutoScalingConfig> {
+        self.inner.get_auto_scaling(config_id).await
+    }
+    async fn list_auto_scaling(&self) -> ComputeResult<Vec<AutoScalingConfig>> {
+        self.inner.list_auto_scaling().await
+    }
+
+    async fn analyze_resources(&self, resource_ids: Vec<String>) -> ComputeResult<Vec<OptimizationStrategy>> {
+        self.inner.analyze_resources(resource_ids).await
+    }
+    async fn apply_optimization(&self, strategy: OptimizationStrategy) -> ComputeResult<()> {
+        self.inner.apply_optimization(strategy).await?;
+        self.cache.invalidate_account(&self.account);
+        Ok(())
+    }
+    async fn get_optimization_history(&self, resource_id: &str) -> ComputeResult<Vec<OptimizationStrategy>> {
+        self.inner.get_optimization_history(resource_id).await
+    }
+
+    async fn get_metrics(&self, resource_id: &str, metric_names: Vec<String>) -> ComputeResult<Vec<(String, f64)>> {
+        self.inner.get_metrics(resource_id, metric_names).await
+    }
+    async fn get_logs(&self, resource_id: &str, start_time: i64, end_time: i64) -> ComputeResult<Vec<String>> {
+        self.inner.get_logs(resource_id, start_time, end_time).await
+    }
+}
@@ -0,0 +1,222 @@
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use sirsi_key_vault::credential::ingest_field;
+use sirsi_key_vault::secret::SecretManager;
+use sirsi_key_vault::{Credential, KeyVaultError, SecretString};
+use tracing::info;
+
+use crate::error::{ComputeError, ComputeResult};
+
+// Provider credentials as stored in config. Secret fields are key-vault references resolved
+// when a provider is built; identifiers (access key id, subscription, tenant, project) are not
+// secret and stay in the clear.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct Credentials {
+    pub access_key: Option<String>,
+    pub secret_key: Option<Credential>,
+    pub token: Option<Credential>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<Credential>,
+    pub tenant_id: Option<String>,
+    pub project_id: Option<String>,
+    pub json_key: Option<Credential>,
+}
+
+// Credentials with every secret fetched, handed to provider SDK clients at init and dropped
+// (and wiped) with them. Intentionally not Debug or Serialize.
+#[derive(Clone, Default)]
+pub struct ResolvedCredentials {
+    pub access_key: Option<String>,
+    pub secret_key: Option<SecretString>,
+    pub token: Option<SecretString>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<SecretString>,
+    pub tenant_id: Option<String>,
+    pub project_id: Option<String>,
+    pub json_key: Option<SecretString>,
+}
+
+// The secret manager providers resolve credentials through; never serialized with the config
+#[derive(Clone)]
+pub struct SecretBroker(pub Arc<dyn SecretManager>);
+
+impl fmt::Debug for SecretBroker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretBroker")
+    }
+}
+
+fn vault_error(e: KeyVaultError) -> ComputeError {
+    match e {
+        KeyVaultError::NotFound(msg) => ComputeError::Config(format!("Credential secret missing: {}", msg)),
+        KeyVaultError::Permission(msg) => ComputeError::Auth(msg),
+        e => ComputeError::Config(format!("Failed to resolve credentials: {}", e)),
+    }
+}
+
+async fn resolve_field(
+    credential: &Option<Credential>,
+    secrets: Option<&dyn SecretManager>,
+) -> ComputeResult<Option<SecretString>> {
+    match credential {
+        Some(credential) => credential.resolve(secrets).await.map(Some).map_err(vault_error),
+        None => Ok(None),
+    }
+}
+
+impl Credentials {
+    pub fn has_plaintext(&self) -> bool {
+        [&self.secret_key, &self.token, &self.client_secret, &self.json_key]
+            .into_iter()
+            .flatten()
+            .any(Credential::is_plaintext)
+    }
+
+    // Fetches current secret values; call at provider init so a rotation in the vault is
+    // picked up by the next provider built
+    pub async fn resolve(&self, secrets: Option<&dyn SecretManager>) -> ComputeResult<ResolvedCredentials> {
+        Ok(ResolvedCredentials {
+            access_key: self.access_key.clone(),
+            secret_key: resolve_field(&self.secret_key, secrets).await?,
+            token: resolve_field(&self.token, secrets).await?,
+            client_id: self.client_id.clone(),
+            client_secret: resolve_field(&self.client_secret, secrets).await?,
+            tenant_id: self.tenant_id.clone(),
+            project_id: self.project_id.clone(),
+            json_key: resolve_field(&self.json_key, secrets).await?,
+        })
+    }
+
+    // Moves any plaintext secrets into the vault as `<prefix>/<field>` and replaces them with
+    // references; returns how many fields were migrated
+    pub async fn migrate(&mut self, secrets: &dyn SecretManager, prefix: &str) -> ComputeResult<usize> {
+        let mut migrated = 0;
+        for (field, credential) in [
+            ("secret_key", &mut self.secret_key),
+            ("token", &mut self.token),
+            ("client_secret", &mut self.client_secret),
+            ("json_key", &mut self.json_key),
+        ] {
+            if ingest_field(credential, secrets, prefix, field).await.map_err(vault_error)? {
+                migrated += 1;
+            }
+        }
+        Ok(migrated)
+    }
+}
+
+// Rewrites a JSON or YAML config file so it holds references instead of plaintext secrets.
+// `credentials` picks the embedded `Credentials` out of whatever config type the file holds.
+// The file is replaced atomically, and only when something changed.
+pub async fn migrate_config_file<T, F>(
+    path: &Path,
+    secrets: &dyn SecretManager,
+    prefix: &str,
+    credentials: F,
+) -> ComputeResult<usize>
+where
+    T: Serialize + for<'de> Deserialize<'de>,
+    F: FnOnce(&mut T) -> &mut Credentials,
+{
+    let raw = tokio::fs::read_to_string(path)
+        .await
+        .map_err(|e| ComputeError::Config(format!("Failed to read {}: {}", path.display(), e)))?;
+    let yaml = matches!(path.extension().and_then(|e| e.to_str()), Some("yaml" | "yml"));
+    let mut config: T = if yaml {
+        serde_yaml::from_str(&raw).map_err(|e| ComputeError::Config(format!("Invalid config {}: {}", path.display(), e)))?
+    } else {
+        serde_json::from_str(&raw).map_err(|e| ComputeError::Config(format!("Invalid config {}: {}", path.display(), e)))?
+    };
+
+    let migrated = credentials(&mut config).migrate(secrets, prefix).await?;
+    if migrated == 0 {
+        return Ok(0);
+    }
+    let rewritten = if yaml {
+        serde_yaml::to_string(&config).map_err(|e| ComputeError::Internal(e.to_string()))?
+    } else {
+        serde_json::to_string_pretty(&config).map_err(|e| ComputeError::Internal(e.to_string()))?
+    };
+    let partial = path.with_extension("migrating");
+    tokio::fs::write(&partial, rewritten)
+        .await
+        .map_err(|e| ComputeError::Internal(format!("Failed to write {}: {}", partial.display(), e)))?;
+    tokio::fs::rename(&partial, path)
+        .await
+        .map_err(|e| ComputeError::Internal(format!("Failed to replace {}: {}", path.display(), e)))?;
+    info!("Moved {} plaintext credential(s) from {} into key-vault", migrated, path.display());
+    Ok(migrated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sirsi_key_vault::secret::{InMemorySecretManager, SecretValue};
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct ProviderFile {
+        region: String,
+        credentials: Credentials,
+    }
+
+    #[tokio::test]
+    async fn test_migrated_config_holds_no_secret_material() {
+        let secrets = InMemorySecretManager::new();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("aws.json");
+        std::fs::write(
+            &path,
+            r#"{"region":"us-east-1","credentials":{"access_key":"AKIAEXAMPLE","secret_key":"wJalrXUtnFEMI-secret","token":"session-token-secret"}}"#,
+        )
+        .unwrap();
+
+        let loaded: ProviderFile = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert!(loaded.credentials.has_plaintext());
+        assert!(!format!("{:?}", loaded).contains("wJalrXUtnFEMI"));
+        assert!(serde_json::to_string(&loaded).is_err());
+
+        let migrated = migrate_config_file(&path, &secrets, "aws/prod", |c: &mut ProviderFile| &mut c.credentials)
+            .await
+            .unwrap();
+        assert_eq!(migrated, 2);
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert!(written.contains("vault:aws/prod/secret_key") && written.contains("AKIAEXAMPLE"));
+        assert!(!written.contains("wJalrXUtnFEMI") && !written.contains("session-token-secret"));
+        // Already migrated, so a second run leaves the file alone
+        assert_eq!(
+            migrate_config_file(&path, &secrets, "aws/prod", |c: &mut ProviderFile| &mut c.credentials).await.unwrap(),
+            0
+        );
+
+        let reloaded: ProviderFile = serde_json::from_str(&written).unwrap();
+        assert!(!reloaded.credentials.has_plaintext());
+        assert!(!format!("{:?}", reloaded).contains("wJalrXUtnFEMI"));
+        assert!(!serde_json::to_string(&reloaded).unwrap().contains("wJalrXUtnFEMI"));
+    }
+
+    #[tokio::test]
+    async fn test_vault_rotation_picked_up_on_next_resolve() {
+        let secrets = InMemorySecretManager::new();
+        let mut credentials = Credentials {
+            project_id: Some("sirsi-prod".into()),
+            json_key: Some(Credential::plaintext(r#"{"private_key":"first"}"#)),
+            ..Default::default()
+        };
+        credentials.migrate(&secrets, "gcp/prod").await.unwrap();
+
+        let first = credentials.resolve(Some(&secrets)).await.unwrap();
+        assert_eq!(first.json_key.unwrap().expose(), r#"{"private_key":"first"}"#);
+
+        let mut rotated = secrets.get_secret("gcp/prod/json_key").await.unwrap();
+        rotated.value = SecretValue::Plain(r#"{"private_key":"second"}"#.into());
+        secrets.update_secret(rotated).await.unwrap();
+        let second = credentials.resolve(Some(&secrets)).await.unwrap();
+        assert_eq!(second.json_key.unwrap().expose(), r#"{"private_key":"second"}"#);
+        assert_eq!(second.project_id.as_deref(), Some("sirsi-prod"));
+
+        assert!(matches!(credentials.resolve(None).await, Err(ComputeError::Config(_))));
+    }
+}
@@ -0,0 +1,152 @@
+use sirsi_common::{ErrorKind, Retryable};
+use thiserror::Error;
+use tonic::Status;
+
+#[derive(Error, Debug)]
+pub enum ComputeError {
+    #[error("Provider error: {0}")]
+    Provider(String),
+
+    #[error("Request throttled: {0}")]
+    Throttled(String),
+
+    #[error("Provider unavailable: {0}")]
+    Unavailable(String),
+
+    #[error("Authentication error: {0}")]
+    Auth(String),
+
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
+    #[error("Validation error: {0}")]
+    Validation(String),
+
+    #[error("Payload too large: {0}")]
+    TooLarge(String),
+
+    #[error("Resource not found: {0}")]
+    NotFound(String),
+
+    #[error("Configuration error: {0}")]
+    Config(String),
+
+    #[error("Internal error: {0}")]
+    Internal(String),
+}
+
+impl ComputeError {
+    pub fn from_kind(kind: ErrorKind, msg: impl Into<String>) -> Self {
+        let msg = msg.into();
+        match kind {
+            ErrorKind::Throttled => ComputeError::Throttled(msg),
+            ErrorKind::ProviderOutage => ComputeError::Unavailable(msg),
+            ErrorKind::AuthFailure => ComputeError::Auth(msg),
+            ErrorKind::NotFound => ComputeError::NotFound(msg),
+            ErrorKind::Conflict => ComputeError::Conflict(msg),
+            ErrorKind::InvalidInput => ComputeError::Validation(msg),
+            ErrorKind::Internal => ComputeError::Provider(msg),
+        }
+    }
+
+    pub fn from_aws_code(code: Option<&str>, msg: impl Into<String>) -> Self {
+        let kind = code.map(ErrorKind::from_aws_code).unwrap_or(ErrorKind::Internal);
+        Self::from_kind(kind, msg)
+    }
+
+    pub fn from_gcp_status(http_code: Option<u16>, status: Option<&str>, msg: impl Into<String>) -> Self {
+        Self::from_kind(ErrorKind::from_gcp_status(http_code, status), msg)
+    }
+
+    pub fn from_http_status(status: u16, msg: impl Into<String>) -> Self {
+        Self::from_kind(ErrorKind::from_http_status(status), msg)
+    }
+}
+
+impl Retryable for ComputeError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            ComputeError::Provider(_) => ErrorKind::Internal,
+            ComputeError::Throttled(_) => ErrorKind::Throttled,
+            ComputeError::Unavailable(_) => ErrorKind::ProviderOutage,
+            ComputeError::Auth(_) => ErrorKind::AuthFailure,
+            ComputeError::Conflict(_) => ErrorKind::Conflict,
+            ComputeError::Validation(_) | ComputeError::TooLarge(_) => ErrorKind::InvalidInput,
+            ComputeError::NotFound(_) => ErrorKind::NotFound,
+            ComputeError::Config(_) => ErrorKind::InvalidInput,
+            ComputeError::Internal(_) => ErrorKind::Internal,
+        }
+    }
+}
+
+impl From<ComputeError> for Status {
+    fn from(error: ComputeError) -> Self {
+        match error {
+            ComputeError::Provider(msg) => Status::internal(msg),
+            ComputeError::Throttled(msg) => Status::resource_exhausted(msg),
+            ComputeError::Unavailable(msg) => Status::unavailable(msg),
+            ComputeError::Auth(msg) => Status::unauthenticated(msg),
+            ComputeError::Conflict(msg) => Status::aborted(msg),
+            ComputeError::Validation(msg) => Status::invalid_argument(msg),
+            ComputeError::TooLarge(msg) => Status::out_of_range(msg),
+            ComputeError::NotFound(msg) => Status::not_found(msg),
+            ComputeError::Config(msg) => Status::failed_precondition(msg),
+            ComputeError::Internal(msg) => Status::internal(msg),
+        }
+    }
+}
+
+// The inverse of the mapping above, for errors coming back from a remote compute-manager
+impl From<Status> for ComputeError {
+    fn from(status: Status) -> Self {
+        let msg = status.message().to_string();
+        match status.code() {
+            tonic::Code::ResourceExhausted => ComputeError::Throttled(msg),
+            tonic::Code::Unavailable | tonic::Code::DeadlineExceeded => ComputeError::Unavailable(msg),
+            tonic::Code::Unauthenticated | tonic::Code::PermissionDenied => ComputeError::Auth(msg),
+            tonic::Code::Aborted | tonic::Code::AlreadyExists => ComputeError::Conflict(msg),
+            tonic::Code::InvalidArgument => ComputeError::Validation(msg),
+            tonic::Code::OutOfRange => ComputeError::TooLarge(msg),
+            tonic::Code::NotFound => ComputeError::NotFound(msg),
+            tonic::Code::FailedPrecondition => ComputeError::Config(msg),
+            _ => ComputeError::Internal(msg),
+        }
+    }
+}
+
+pub type ComputeResult<T> = Result<T, ComputeError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aws_errors_classify() {
+        let err = ComputeError::from_aws_code(Some("RequestLimitExceeded"), "Failed to start instance");
+        assert!(matches!(err, ComputeError::Throttled(_)));
+        assert!(err.is_retryable());
+
+        let err = ComputeError::from_aws_code(Some("InvalidInstanceID.NotFound"), "Failed to get instance");
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        assert!(!err.is_retryable());
+
+        let err = ComputeError::from_aws_code(None, "Failed to list instances");
+        assert_eq!(err.kind(), ErrorKind::Internal);
+    }
+
+    #[test]
+    fn test_gcp_errors_classify() {
+        assert!(ComputeError::from_gcp_status(Some(429), None, "quota").is_retryable());
+        assert!(ComputeError::from_gcp_status(Some(503), None, "backend").is_retryable());
+        assert_eq!(
+            ComputeError::from_gcp_status(Some(409), Some("ALREADY_EXISTS"), "template").kind(),
+            ErrorKind::Conflict
+        );
+    }
+
+    #[test]
+    fn test_status_mapping() {
+        let status: Status = ComputeError::Throttled("slow down".into()).into();
+        assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+    }
+}
@@ -0,0 +1,712 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use tokio::task::JoinSet;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::error::{ComputeError, ComputeResult};
+use super::{Instance, InstanceState};
+
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CommandPlatform {
+    #[default]
+    Linux,
+    Windows,
+}
+
+// What each backend is asked to run on one instance
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RemoteCommand {
+    pub script: String,
+    pub platform: CommandPlatform,
+    pub working_directory: Option<String>,
+    pub timeout_seconds: u64,
+}
+
+impl RemoteCommand {
+    // For backends that only take a script body
+    pub fn script_in_working_directory(&self) -> String {
+        match (&self.working_directory, self.platform) {
+            (None, _) => self.script.clone(),
+            (Some(dir), CommandPlatform::Linux) => format!("cd '{}' && {}", dir.replace('\'', "'\\''"), self.script),
+            (Some(dir), CommandPlatform::Windows) => format!("Set-Location -LiteralPath '{}'\n{}", dir.replace('\'', "''"), self.script),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CommandOptions {
+    pub max_concurrency: usize,
+    pub timeout_seconds: u64,
+    // Stop starting new instances once more than this share of all targets has failed
+    pub max_failure_percentage: Option<f64>,
+    // Output beyond this is cut from the result and kept whole in the output store
+    pub inline_output_bytes: usize,
+    pub platform: CommandPlatform,
+    pub working_directory: Option<String>,
+}
+
+impl Default for CommandOptions {
+    fn default() -> Self {
+        Self {
+            max_concurrency: 10,
+            timeout_seconds: 600,
+            max_failure_percentage: None,
+            inline_output_bytes: 24 * 1024,
+            platform: CommandPlatform::Linux,
+            working_directory: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InvocationStatus {
+    Running,
+    Succeeded,
+    Failed,
+    Aborted,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TargetStatus {
+    Pending,
+    Succeeded,
+    Failed,
+    TimedOut,
+    // Not started: the instance wasn't running, or the invocation aborted first
+    Skipped,
+}
+
+impl TargetStatus {
+    pub fn is_failure(&self) -> bool {
+        matches!(self, TargetStatus::Failed | TargetStatus::TimedOut)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TargetResult {
+    pub instance_id: String,
+    pub status: TargetStatus,
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    pub output_truncated: bool,
+    pub stdout_location: Option<String>,
+    pub stderr_location: Option<String>,
+    pub error: Option<String>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+impl TargetResult {
+    fn new(instance_id: &str, status: TargetStatus) -> Self {
+        Self {
+            instance_id: instance_id.to_string(),
+            status,
+            exit_code: None,
+            stdout: String::new(),
+            stderr: String::new(),
+            output_truncated: false,
+            stdout_location: None,
+            stderr_location: None,
+            error: None,
+            started_at: None,
+            completed_at: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InvocationSummary {
+    pub targets: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub timed_out: usize,
+    pub skipped: usize,
+    pub pending: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommandInvocation {
+    pub id: String,
+    pub fleet_id: String,
+    pub group_id: String,
+    pub command: String,
+    pub requested_by: String,
+    pub options: CommandOptions,
+    pub status: InvocationStatus,
+    pub targets: Vec<TargetResult>,
+    pub created_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+impl CommandInvocation {
+    pub fn summary(&self) -> InvocationSummary {
+        let mut summary = InvocationSummary { targets: self.targets.len(), ..Default::default() };
+        for target in &self.targets {
+            match target.status {
+                TargetStatus::Pending => summary.pending += 1,
+                TargetStatus::Succeeded => summary.succeeded += 1,
+                TargetStatus::Failed => summary.failed += 1,
+                TargetStatus::TimedOut => summary.timed_out += 1,
+                TargetStatus::Skipped => summary.skipped += 1,
+            }
+        }
+        summary
+    }
+
+    fn record(&mut self, result: TargetResult) {
+        if let Some(slot) = self.targets.iter_mut().find(|t| t.instance_id == result.instance_id) {
+            *slot = result;
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandOutput {
+    pub exit_code: i32,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandPoll {
+    Running,
+    Finished(CommandOutput),
+}
+
+// The provider's agent channel: SSM RunCommand, Azure Run Command or GCP OS Config
+#[async_trait]
+pub trait CommandBackend: Send + Sync {
+    async fn list_instances(&self, fleet_id: &str, group_id: &str) -> ComputeResult<Vec<Instance>>;
+    // Starts the command on one instance and returns the provider's handle for it
+    async fn start(&self, instance: &Instance, command: &RemoteCommand) -> ComputeResult<String>;
+    async fn poll(&self, instance: &Instance, handle: &str) -> ComputeResult<CommandPoll>;
+    async fn cancel(&self, instance: &Instance, handle: &str) -> ComputeResult<()>;
+}
+
+// Where full output goes when it is too long to keep inline; returns a location for the result
+#[async_trait]
+pub trait OutputStore: Send + Sync {
+    async fn put(&self, key: &str, data: Vec<u8>) -> ComputeResult<String>;
+}
+
+pub struct FileOutputStore {
+    root: PathBuf,
+}
+
+impl FileOutputStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+#[async_trait]
+impl OutputStore for FileOutputStore {
+    async fn put(&self, key: &str, data: Vec<u8>) -> ComputeResult<String> {
+        let path = self.root.join(key);
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|e| ComputeError::Internal(format!("Failed to store command output: {}", e)))?;
+        }
+        tokio::fs::write(&path, data)
+            .await
+            .map_err(|e| ComputeError::Internal(format!("Failed to store command output: {}", e)))?;
+        Ok(format!("file://{}", path.display()))
+    }
+}
+
+#[async_trait]
+pub trait CommandInvocationStore: Send + Sync {
+    async fn save_invocation(&self, invocation: &CommandInvocation) -> ComputeResult<()>;
+    async fn get_invocation(&self, invocation_id: &str) -> ComputeResult<Option<CommandInvocation>>;
+    async fn list_invocations(&self, fleet_id: &str) -> ComputeResult<Vec<CommandInvocation>>;
+}
+
+#[derive(Default)]
+pub struct InMemoryCommandInvocationStore {
+    invocations: RwLock<HashMap<String, CommandInvocation>>,
+}
+
+impl InMemoryCommandInvocationStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl CommandInvocationStore for InMemoryCommandInvocationStore {
+    async fn save_invocation(&self, invocation: &CommandInvocation) -> ComputeResult<()> {
+        self.invocations.write().await.insert(invocation.id.clone(), invocation.clone());
+        Ok(())
+    }
+
+    async fn get_invocation(&self, invocation_id: &str) -> ComputeResult<Option<CommandInvocation>> {
+        Ok(self.invocations.read().await.get(invocation_id).cloned())
+    }
+
+    async fn list_invocations(&self, fleet_id: &str) -> ComputeResult<Vec<CommandInvocation>> {
+        let mut invocations: Vec<CommandInvocation> = self
+            .invocations
+            .read()
+            .await
+            .values()
+            .filter(|i| i.fleet_id == fleet_id)
+            .cloned()
+            .collect();
+        invocations.sort_by_key(|invocation| std::cmp::Reverse(invocation.created_at));
+        Ok(invocations)
+    }
+}
+
+// Cuts at a char boundary so the inline part stays valid UTF-8
+fn truncate_output(output: &str, max_bytes: usize) -> &str {
+    if output.len() <= max_bytes {
+        return output;
+    }
+    let mut end = max_bytes;
+    while !output.is_char_boundary(end) {
+        end -= 1;
+    }
+    &output[..end]
+}
+
+struct TargetRun {
+    backend: Arc<dyn CommandBackend>,
+    outputs: Arc<dyn OutputStore>,
+    command: RemoteCommand,
+    invocation_id: String,
+    inline_output_bytes: usize,
+    poll_interval: Duration,
+}
+
+impl TargetRun {
+    async fn run(&self, instance: Instance) -> TargetResult {
+        let mut result = TargetResult::new(&instance.instance_id, TargetStatus::Failed);
+        result.started_at = Some(Utc::now());
+        let handle = match self.backend.start(&instance, &self.command).await {
+            Ok(handle) => handle,
+            Err(e) => {
+                result.error = Some(e.to_string());
+                result.completed_at = Some(Utc::now());
+                return result;
+            }
+        };
+
+        let timeout = Duration::from_secs(self.command.timeout_seconds);
+        match tokio::time::timeout(timeout, self.wait(&instance, &handle)).await {
+            Ok(Ok(output)) => {
+                result.status = if output.exit_code == 0 { TargetStatus::Succeeded } else { TargetStatus::Failed };
+                result.exit_code = Some(output.exit_code);
+                self.attach_output(&mut result, output).await;
+            }
+            Ok(Err(e)) => result.error = Some(e.to_string()),
+            Err(_) => {
+                result.status = TargetStatus::TimedOut;
+                result.error = Some(format!("Command did not finish within {}s", self.command.timeout_seconds));
+                if let Err(e) = self.backend.cancel(&instance, &handle).await {
+                    warn!("Failed to cancel command {} on {}: {}", handle, instance.instance_id, e);
+                }
+            }
+        }
+        result.completed_at = Some(Utc::now());
+        result
+    }
+
+    async fn wait(&self, instance: &Instance, handle: &str) -> ComputeResult<CommandOutput> {
+        loop {
+            if let CommandPoll::Finished(output) = self.backend.poll(instance, handle).await? {
+                return Ok(output);
+            }
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+
+    async fn attach_output(&self, result: &mut TargetResult, output: CommandOutput) {
+        let limit = self.inline_output_bytes;
+        if output.stdout.len() + output.stderr.len() <= limit {
+            result.stdout = output.stdout;
+            result.stderr = output.stderr;
+            return;
+        }
+
+        result.output_truncated = true;
+        result.stdout = truncate_output(&output.stdout, limit).to_string();
+        result.stderr = truncate_output(&output.stderr, limit.saturating_sub(result.stdout.len())).to_string();
+        let prefix = format!("commands/{}/{}", self.invocation_id, result.instance_id);
+        for (name, data, location) in [
+            ("stdout", output.stdout, &mut result.stdout_location),
+            ("stderr", output.stderr, &mut result.stderr_location),
+        ] {
+            if data.is_empty() {
+                continue;
+            }
+            match self.outputs.put(&format!("{}/{}.log", prefix, name), data.into_bytes()).await {
+                Ok(stored) => *location = Some(stored),
+                Err(e) => warn!("Failed to store full {} for {}: {}", name, result.instance_id, e),
+            }
+        }
+    }
+}
+
+pub struct CommandRunner {
+    backend: Arc<dyn CommandBackend>,
+    store: Arc<dyn CommandInvocationStore>,
+    outputs: Arc<dyn OutputStore>,
+    poll_interval: Duration,
+}
+
+impl CommandRunner {
+    pub fn new(
+        backend: Arc<dyn CommandBackend>,
+        store: Arc<dyn CommandInvocationStore>,
+        outputs: Arc<dyn OutputStore>,
+    ) -> Self {
+        Self { backend, store, outputs, poll_interval: DEFAULT_POLL_INTERVAL }
+    }
+
+    pub fn with_poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    // Records the invocation and starts it in the background; poll `get_invocation` for results
+    pub async fn run_command(
+        self: &Arc<Self>,
+        fleet_id: &str,
+        group_id: &str,
+        command: &str,
+        options: CommandOptions,
+        requested_by: &str,
+    ) -> ComputeResult<CommandInvocation> {
+        let invocation = self.prepare(fleet_id, group_id, command, options, requested_by).await?;
+        let runner = self.clone();
+        let started = invocation.clone();
+        tokio::spawn(async move {
+            let id = started.id.clone();
+            if let Err(e) = runner.execute(started).await {
+                warn!("Command invocation {} failed: {}", id, e);
+            }
+        });
+        Ok(invocation)
+    }
+
+    pub async fn get_invocation(&self, invocation_id: &str) -> ComputeResult<CommandInvocation> {
+        self.store
+            .get_invocation(invocation_id)
+            .await?
+            .ok_or_else(|| ComputeError::NotFound(format!("Command invocation {} not found", invocation_id)))
+    }
+
+    pub async fn prepare(
+        &self,
+        fleet_id: &str,
+        group_id: &str,
+        command: &str,
+        options: CommandOptions,
+        requested_by: &str,
+    ) -> ComputeResult<CommandInvocation> {
+        if command.trim().is_empty() {
+            return Err(ComputeError::Validation("Command must not be empty".into()));
+        }
+        if options.max_concurrency == 0 || options.timeout_seconds == 0 {
+            return Err(ComputeError::Validation("max_concurrency and timeout_seconds must be at least 1".into()));
+        }
+        if options.max_failure_percentage.is_some_and(|p| !(0.0..=100.0).contains(&p)) {
+            return Err(ComputeError::Validation("max_failure_percentage must be between 0 and 100".into()));
+        }
+
+        let instances = self.backend.list_instances(fleet_id, group_id).await?;
+        if instances.is_empty() {
+            return Err(ComputeError::NotFound(format!("No instances in group {} of fleet {}", group_id, fleet_id)));
+        }
+        let targets = instances
+            .iter()
+            .map(|instance| {
+                let status = match instance.state {
+                    InstanceState::Running => TargetStatus::Pending,
+                    _ => TargetStatus::Skipped,
+                };
+                TargetResult::new(&instance.instance_id, status)
+            })
+            .collect();
+
+        let invocation = CommandInvocation {
+            id: Uuid::new_v4().to_string(),
+            fleet_id: fleet_id.to_string(),
+            group_id: group_id.to_string(),
+            command: command.to_string(),
+            requested_by: requested_by.to_string(),
+            options,
+            status: InvocationStatus::Running,
+            targets,
+            created_at: Utc::now(),
+            completed_at: None,
+        };
+        self.store.save_invocation(&invocation).await?;
+        Ok(invocation)
+    }
+
+    // Fans out over the running instances at most `max_concurrency` at a time, saving each
+    // result as it lands so progress is visible while the rest are still going
+    pub async fn execute(&self, mut invocation: CommandInvocation) -> ComputeResult<CommandInvocation> {
+        let options = invocation.options.clone();
+        let mut pending: Vec<Instance> = self
+            .backend
+            .list_instances(&invocation.fleet_id, &invocation.group_id)
+            .await?
+            .into_iter()
+            .filter(|instance| {
+                invocation
+                    .targets
+                    .iter()
+                    .any(|t| t.instance_id == instance.instance_id && t.status == TargetStatus::Pending)
+            })
+            .collect();
+        pending.reverse();
+
+        let run = Arc::new(TargetRun {
+            backend: self.backend.clone(),
+            outputs: self.outputs.clone(),
+            command: RemoteCommand {
+                script: invocation.command.clone(),
+                platform: options.platform,
+                working_directory: options.working_directory.clone(),
+                timeout_seconds: options.timeout_seconds,
+            },
+            invocation_id: invocation.id.clone(),
+            inline_output_bytes: options.inline_output_bytes,
+            poll_interval: self.poll_interval,
+        });
+
+        let total = invocation.targets.len() as f64;
+        let mut failures = 0usize;
+        let mut aborted = false;
+        let mut tasks = JoinSet::new();
+        loop {
+            while !aborted && tasks.len() < options.max_concurrency {
+                let Some(instance) = pending.pop() else { break };
+                let run = run.clone();
+                tasks.spawn(async move { run.run(instance).await });
+            }
+            let Some(joined) = tasks.join_next().await else { break };
+            let result = joined.map_err(|e| ComputeError::Internal(format!("Command task failed: {}", e)))?;
+            if result.status.is_failure() {
+                failures += 1;
+                let threshold = options.max_failure_percentage;
+                if !aborted && threshold.is_some_and(|max| failures as f64 * 100.0 / total > max) {
+                    aborted = true;
+                    warn!(
+                        "Aborting command invocation {}: {} of {} targets failed",
+                        invocation.id, failures, invocation.targets.len()
+                    );
+                }
+            }
+            invocation.record(result);
+            self.store.save_invocation(&invocation).await?;
+        }
+
+        for target in invocation.targets.iter_mut().filter(|t| t.status == TargetStatus::Pending) {
+            target.status = TargetStatus::Skipped;
+        }
+        invocation.status = if aborted {
+            InvocationStatus::Aborted
+        } else if failures > 0 {
+            InvocationStatus::Failed
+        } else {
+            InvocationStatus::Succeeded
+        };
+        invocation.completed_at = Some(Utc::now());
+        self.store.save_invocation(&invocation).await?;
+
+        let summary = invocation.summary();
+        info!(
+            "Command invocation {} finished {:?}: {} succeeded, {} failed, {} timed out, {} skipped",
+            invocation.id, invocation.status, summary.succeeded, summary.failed, summary.timed_out, summary.skipped
+        );
+        Ok(invocation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    #[derive(Clone)]
+    enum Behaviour {
+        Exit(i32, String),
+        Hang,
+    }
+
+    // Each started command finishes after `runtime`, except `Hang` ones which never do
+    struct MockBackend {
+        instances: Vec<Instance>,
+        behaviour: HashMap<String, Behaviour>,
+        runtime: Duration,
+        started: Mutex<HashMap<String, tokio::time::Instant>>,
+        running: AtomicUsize,
+        peak: AtomicUsize,
+        cancelled: Mutex<Vec<String>>,
+    }
+
+    impl MockBackend {
+        fn new(count: usize, states: &[(usize, InstanceState)], behaviour: &[(usize, Behaviour)]) -> Self {
+            let instances = (0..count)
+                .map(|i| Instance {
+                    instance_id: format!("i-{:02}", i),
+                    group_id: "web".into(),
+                    fleet_id: "fleet-1".into(),
+                    instance_type: "m5.large".into(),
+                    private_ip: format!("10.0.0.{}", i),
+                    public_ip: None,
+                    state: states.iter().find(|(n, _)| *n == i).map(|(_, s)| s.clone()).unwrap_or(InstanceState::Running),
+                    launch_time: Utc::now(),
+                    labels: HashMap::new(),
+                    metrics: None,
+                })
+                .collect();
+            Self {
+                instances,
+                behaviour: behaviour.iter().map(|(i, b)| (format!("i-{:02}", i), b.clone())).collect(),
+                runtime: Duration::from_secs(5),
+                started: Mutex::new(HashMap::new()),
+                running: AtomicUsize::new(0),
+                peak: AtomicUsize::new(0),
+                cancelled: Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl CommandBackend for MockBackend {
+        async fn list_instances(&self, _fleet_id: &str, _group_id: &str) -> ComputeResult<Vec<Instance>> {
+            Ok(self.instances.clone())
+        }
+
+        async fn start(&self, instance: &Instance, command: &RemoteCommand) -> ComputeResult<String> {
+            assert_eq!(command.script, "systemctl restart nginx");
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(running, Ordering::SeqCst);
+            self.started.lock().unwrap().insert(instance.instance_id.clone(), tokio::time::Instant::now());
+            Ok(format!("cmd-{}", instance.instance_id))
+        }
+
+        async fn poll(&self, instance: &Instance, _handle: &str) -> ComputeResult<CommandPoll> {
+            let started = self.started.lock().unwrap()[&instance.instance_id];
+            let behaviour = self.behaviour.get(&instance.instance_id).cloned().unwrap_or(Behaviour::Exit(0, "ok".into()));
+            match behaviour {
+                Behaviour::Exit(code, stdout) if started.elapsed() >= self.runtime => {
+                    self.running.fetch_sub(1, Ordering::SeqCst);
+                    Ok(CommandPoll::Finished(CommandOutput { exit_code: code, stdout, stderr: String::new() }))
+                }
+                _ => Ok(CommandPoll::Running),
+            }
+        }
+
+        async fn cancel(&self, instance: &Instance, _handle: &str) -> ComputeResult<()> {
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            self.cancelled.lock().unwrap().push(instance.instance_id.clone());
+            Ok(())
+        }
+    }
+
+    fn runner(backend: Arc<MockBackend>, outputs: &std::path::Path) -> Arc<CommandRunner> {
+        Arc::new(
+            CommandRunner::new(backend, Arc::new(InMemoryCommandInvocationStore::new()), Arc::new(FileOutputStore::new(outputs)))
+                .with_poll_interval(Duration::from_secs(1)),
+        )
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_fan_out_is_bounded_and_long_output_is_stored() {
+        let dir = tempfile::tempdir().unwrap();
+        let long_output = "x".repeat(100) + "é";
+        let backend = Arc::new(MockBackend::new(
+            12,
+            &[(11, InstanceState::Stopped)],
+            &[(3, Behaviour::Exit(0, long_output.clone()))],
+        ));
+        let runner = runner(backend.clone(), dir.path());
+        let options = CommandOptions { max_concurrency: 3, inline_output_bytes: 101, ..Default::default() };
+
+        let invocation = runner.prepare("fleet-1", "web", "systemctl restart nginx", options, "alice").await.unwrap();
+        let done = runner.execute(invocation).await.unwrap();
+
+        assert_eq!(backend.peak.load(Ordering::SeqCst), 3);
+        assert_eq!(done.status, InvocationStatus::Succeeded);
+        assert_eq!(done.summary(), InvocationSummary { targets: 12, succeeded: 11, skipped: 1, ..Default::default() });
+        assert_eq!(runner.get_invocation(&done.id).await.unwrap(), done);
+
+        let truncated = done.targets.iter().find(|t| t.instance_id == "i-03").unwrap();
+        assert!(truncated.output_truncated);
+        assert_eq!(truncated.stdout, "x".repeat(100));
+        let location = truncated.stdout_location.as_deref().unwrap();
+        let stored = std::fs::read_to_string(location.strip_prefix("file://").unwrap()).unwrap();
+        assert_eq!(stored, long_output);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_partial_failures_and_timeouts_are_aggregated() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = Arc::new(MockBackend::new(
+            6,
+            &[],
+            &[(1, Behaviour::Exit(2, "config test failed".into())), (4, Behaviour::Hang)],
+        ));
+        let runner = runner(backend.clone(), dir.path());
+        let options = CommandOptions { max_concurrency: 6, timeout_seconds: 30, ..Default::default() };
+
+        let started = runner.run_command("fleet-1", "web", "systemctl restart nginx", options, "alice").await.unwrap();
+        assert_eq!(started.status, InvocationStatus::Running);
+        let done = loop {
+            let current = runner.get_invocation(&started.id).await.unwrap();
+            if current.status != InvocationStatus::Running {
+                break current;
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        };
+
+        assert_eq!(done.status, InvocationStatus::Failed);
+        assert_eq!(done.summary(), InvocationSummary { targets: 6, succeeded: 4, failed: 1, timed_out: 1, ..Default::default() });
+        let failed = done.targets.iter().find(|t| t.instance_id == "i-01").unwrap();
+        assert_eq!((failed.exit_code, failed.stdout.as_str()), (Some(2), "config test failed"));
+        assert_eq!(*backend.cancelled.lock().unwrap(), vec!["i-04".to_string()]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_abort_threshold_stops_new_targets() {
+        let dir = tempfile::tempdir().unwrap();
+        let failing: Vec<(usize, Behaviour)> = (0..5).map(|i| (i, Behaviour::Exit(1, String::new()))).collect();
+        let backend = Arc::new(MockBackend::new(10, &[], &failing));
+        let runner = runner(backend.clone(), dir.path());
+        let options = CommandOptions { max_concurrency: 2, max_failure_percentage: Some(20.0), ..Default::default() };
+
+        let invocation = runner.prepare("fleet-1", "web", "systemctl restart nginx", options, "alice").await.unwrap();
+        let done = runner.execute(invocation).await.unwrap();
+
+        // The third failure crosses 20% of 10; the one already running alongside it still finishes
+        assert_eq!(done.status, InvocationStatus::Aborted);
+        assert_eq!(done.summary(), InvocationSummary { targets: 10, failed: 4, skipped: 6, ..Default::default() });
+        assert!(
+            ["i-05", "i-06", "i-07", "i-08", "i-09"]
+                .iter()
+                .all(|id| done.targets.iter().any(|t| t.instance_id == *id && t.status == TargetStatus::Skipped))
+        );
+    }
+}
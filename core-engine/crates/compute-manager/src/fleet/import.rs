@@ -0,0 +1,521 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::info;
+
+use crate::error::{ComputeError, ComputeResult};
+use super::store::FleetStore;
+use super::{
+    FileSystem, FleetConfig, InstanceGroup, ScalingConfig, ScalingMetric, StorageConfig, VolumeConfig,
+    VolumeType,
+};
+
+pub const MANAGED_BY_TAG: &str = "managed-by";
+pub const MANAGED_BY_VALUE: &str = "sirsi-nexus";
+pub const PROVIDER_ID_ANNOTATION: &str = "sirsi.io/provider-id";
+pub const UNMANAGED_ANNOTATION_PREFIX: &str = "sirsi.io/unmanaged/";
+
+// Provider-neutral snapshot of an ASG, MIG or VMSS together with its launch template
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LiveGroup {
+    pub identifier: String,
+    pub name: String,
+    pub instance_type: String,
+    pub min_size: i32,
+    pub max_size: i32,
+    pub desired_size: i32,
+    #[serde(default)]
+    pub tags: HashMap<String, String>,
+    pub vpc_id: Option<String>,
+    #[serde(default)]
+    pub subnet_ids: Vec<String>,
+    #[serde(default)]
+    pub security_groups: Vec<String>,
+    #[serde(default)]
+    pub associate_public_ip: bool,
+    pub root_volume_size: Option<i32>,
+    #[serde(default)]
+    pub data_volumes: Vec<LiveVolume>,
+    pub user_data: Option<String>,
+    #[serde(default)]
+    pub scaling_policies: Vec<LiveScalingPolicy>,
+    pub cooldown_seconds: Option<i32>,
+    #[serde(default)]
+    pub scale_in_protection: bool,
+    // Set while an instance refresh or rolling replacement is running
+    #[serde(default)]
+    pub update_in_progress: bool,
+    // Raw provider attributes without a FleetConfig counterpart
+    #[serde(default)]
+    pub attributes: BTreeMap<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LiveVolume {
+    pub device_name: String,
+    pub size: i32,
+    pub volume_type: String,
+    pub iops: Option<i32>,
+    pub throughput: Option<i32>,
+    #[serde(default)]
+    pub encrypted: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LiveScalingPolicy {
+    pub name: String,
+    pub metric: String,
+    pub target_value: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnsupportedAttribute {
+    pub identifier: String,
+    pub path: String,
+    pub value: Value,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImportOptions {
+    pub fleet_id: Option<String>,
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportReport {
+    pub fleet: FleetConfig,
+    pub unsupported: Vec<UnsupportedAttribute>,
+    pub dry_run: bool,
+}
+
+// Read/write access to live group resources needed for adoption
+#[async_trait]
+pub trait ImportSource: Send + Sync {
+    async fn describe_group(&self, identifier: &str) -> ComputeResult<LiveGroup>;
+    async fn tag_group(&self, identifier: &str, tags: HashMap<String, String>) -> ComputeResult<()>;
+    // Implementations must only touch the modeled fields of the group
+    async fn update_group(&self, identifier: &str, group: &InstanceGroup) -> ComputeResult<()>;
+}
+
+pub struct FleetImporter {
+    source: Arc<dyn ImportSource>,
+    store: Arc<dyn FleetStore>,
+}
+
+impl FleetImporter {
+    pub fn new(source: Arc<dyn ImportSource>, store: Arc<dyn FleetStore>) -> Self {
+        Self { source, store }
+    }
+
+    pub async fn import_fleet(
+        &self,
+        name: &str,
+        identifiers: &[String],
+        options: ImportOptions,
+    ) -> ComputeResult<ImportReport> {
+        if identifiers.is_empty() {
+            return Err(ComputeError::Validation("At least one resource identifier is required".into()));
+        }
+
+        let fleet_id = options
+            .fleet_id
+            .clone()
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+        if !options.dry_run {
+            if let Some(existing) = self.store.get_fleet(&fleet_id).await? {
+                return Err(ComputeError::Conflict(format!(
+                    "Fleet {} is already managed as {}",
+                    fleet_id, existing.name
+                )));
+            }
+        }
+
+        let mut fleet = FleetConfig::new(fleet_id, name.to_string());
+        let mut unsupported = Vec::new();
+
+        for identifier in identifiers {
+            let live = self.source.describe_group(identifier).await?;
+            let (group, mut group_unsupported) = reconstruct_group(&live);
+
+            if fleet.instance_groups.is_empty() {
+                fleet.network_config.vpc_id = live.vpc_id.clone();
+                fleet.network_config.enable_public_ip = live.associate_public_ip;
+            } else if fleet.network_config.vpc_id != live.vpc_id {
+                return Err(ComputeError::Validation(format!(
+                    "Group {} is in a different VPC than the rest of the fleet",
+                    identifier
+                )));
+            }
+            for subnet in &live.subnet_ids {
+                if !fleet.network_config.subnet_ids.contains(subnet) {
+                    fleet.network_config.subnet_ids.push(subnet.clone());
+                }
+            }
+            for sg in &live.security_groups {
+                if !fleet.network_config.security_groups.contains(sg) {
+                    fleet.network_config.security_groups.push(sg.clone());
+                }
+            }
+
+            unsupported.append(&mut group_unsupported);
+            fleet.add_instance_group(group);
+        }
+
+        fleet
+            .labels
+            .insert(MANAGED_BY_TAG.to_string(), MANAGED_BY_VALUE.to_string());
+
+        if !options.dry_run {
+            for identifier in identifiers {
+                let mut tags = HashMap::new();
+                tags.insert(MANAGED_BY_TAG.to_string(), MANAGED_BY_VALUE.to_string());
+                self.source.tag_group(identifier, tags).await?;
+            }
+            self.store.save_fleet(&fleet).await?;
+            info!(
+                "Imported fleet {} from {} group(s), {} unsupported attribute(s)",
+                fleet.id,
+                identifiers.len(),
+                unsupported.len()
+            );
+        }
+
+        Ok(ImportReport {
+            fleet,
+            unsupported,
+            dry_run: options.dry_run,
+        })
+    }
+
+    // Applies changes to an imported fleet, keeping the attributes flagged as unmanaged
+    pub async fn update_fleet(&self, desired: FleetConfig) -> ComputeResult<FleetConfig> {
+        let stored = self
+            .store
+            .get_fleet(&desired.id)
+            .await?
+            .ok_or_else(|| ComputeError::NotFound(format!("Fleet {} is not managed", desired.id)))?;
+
+        let merged = preserve_unmanaged(&stored, desired);
+
+        for group in &merged.instance_groups {
+            self.source.update_group(provider_identifier(group), group).await?;
+        }
+
+        self.store.save_fleet(&merged).await?;
+        Ok(merged)
+    }
+}
+
+pub fn provider_identifier(group: &InstanceGroup) -> &str {
+    group
+        .annotations
+        .get(PROVIDER_ID_ANNOTATION)
+        .map(String::as_str)
+        .unwrap_or(&group.id)
+}
+
+pub fn unmanaged_attributes(group: &InstanceGroup) -> BTreeMap<String, String> {
+    group
+        .annotations
+        .iter()
+        .filter_map(|(k, v)| {
+            k.strip_prefix(UNMANAGED_ANNOTATION_PREFIX)
+                .map(|path| (path.to_string(), v.clone()))
+        })
+        .collect()
+}
+
+fn preserve_unmanaged(stored: &FleetConfig, mut desired: FleetConfig) -> FleetConfig {
+    for group in desired.instance_groups.iter_mut() {
+        if let Some(previous) = stored.get_instance_group(&group.id) {
+            for (key, value) in &previous.annotations {
+                if key.starts_with(UNMANAGED_ANNOTATION_PREFIX) || key == PROVIDER_ID_ANNOTATION {
+                    group.annotations.insert(key.clone(), value.clone());
+                }
+            }
+        }
+    }
+
+    if let Some(value) = stored.labels.get(MANAGED_BY_TAG) {
+        desired.labels.insert(MANAGED_BY_TAG.to_string(), value.clone());
+    }
+
+    desired
+}
+
+fn reconstruct_group(live: &LiveGroup) -> (InstanceGroup, Vec<UnsupportedAttribute>) {
+    let mut unsupported = Vec::new();
+    let mut flag = |group: &mut InstanceGroup, path: String, value: Value| {
+        group.annotations.insert(
+            format!("{}{}", UNMANAGED_ANNOTATION_PREFIX, path),
+            value.to_string(),
+        );
+        unsupported.push(UnsupportedAttribute {
+            identifier: live.identifier.clone(),
+            path,
+            value,
+        });
+    };
+
+    let mut group = InstanceGroup::new(live.name.clone(), live.name.clone(), live.instance_type.clone())
+        .with_scaling(live.min_size, live.max_size, live.desired_size);
+    group
+        .annotations
+        .insert(PROVIDER_ID_ANNOTATION.to_string(), live.identifier.clone());
+    group.startup_script = live.user_data.clone();
+
+    for (key, value) in &live.tags {
+        // Provider-reserved tags are read-only and not worth tracking
+        if key.starts_with("aws:") || key.starts_with("goog-") {
+            continue;
+        }
+        if key == MANAGED_BY_TAG && value != MANAGED_BY_VALUE {
+            flag(&mut group, format!("tags.{}", key), Value::String(value.clone()));
+            continue;
+        }
+        group.labels.insert(key.clone(), value.clone());
+    }
+
+    let mut data_volumes = Vec::new();
+    for volume in &live.data_volumes {
+        match parse_volume_type(&volume.volume_type) {
+            Some(volume_type) => {
+                if volume.encrypted {
+                    flag(
+                        &mut group,
+                        format!("volumes.{}.encrypted", volume.device_name),
+                        Value::Bool(true),
+                    );
+                }
+                data_volumes.push(VolumeConfig {
+                    size: volume.size,
+                    volume_type,
+                    iops: volume.iops,
+                    throughput: volume.throughput,
+                    mount_path: volume.device_name.clone(),
+                    file_system: FileSystem::Ext4,
+                });
+            }
+            None => flag(
+                &mut group,
+                format!("volumes.{}", volume.device_name),
+                serde_json::to_value(volume).unwrap_or(Value::Null),
+            ),
+        }
+    }
+    group.storage_config = StorageConfig {
+        root_volume_size: live.root_volume_size.unwrap_or(group.storage_config.root_volume_size),
+        data_volumes,
+    };
+
+    if !live.scaling_policies.is_empty() || live.scale_in_protection {
+        group.scaling_config = Some(ScalingConfig {
+            metrics: live
+                .scaling_policies
+                .iter()
+                .map(|p| ScalingMetric {
+                    name: p.metric.clone(),
+                    target_value: p.target_value,
+                    scale_out_threshold: p.target_value,
+                    scale_in_threshold: p.target_value,
+                    evaluation_periods: 1,
+                })
+                .collect(),
+            cooldown_seconds: live.cooldown_seconds.unwrap_or(300),
+            scale_in_protection: live.scale_in_protection,
+        });
+    }
+
+    for (key, value) in &live.attributes {
+        flag(&mut group, format!("attributes.{}", key), value.clone());
+    }
+
+    (group, unsupported)
+}
+
+fn parse_volume_type(volume_type: &str) -> Option<VolumeType> {
+    match volume_type {
+        "gp2" => Some(VolumeType::Gp2),
+        "gp3" => Some(VolumeType::Gp3),
+        "io1" => Some(VolumeType::Io1),
+        "io2" => Some(VolumeType::Io2),
+        "st1" => Some(VolumeType::St1),
+        "sc1" => Some(VolumeType::Sc1),
+        "standard" => Some(VolumeType::Standard),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fleet::store::InMemoryFleetStore;
+    use std::sync::Mutex;
+
+    const FIXTURE_ASG: &str = r#"{
+        "identifier": "arn:aws:autoscaling:us-east-1:123456789012:autoScalingGroup:web-asg",
+        "name": "web-asg",
+        "instance_type": "m5.large",
+        "min_size": 2,
+        "max_size": 6,
+        "desired_size": 3,
+        "tags": {
+            "team": "web",
+            "aws:cloudformation:stack-name": "legacy-web"
+        },
+        "vpc_id": "vpc-0abc",
+        "subnet_ids": ["subnet-1", "subnet-2"],
+        "security_groups": ["sg-web"],
+        "associate_public_ip": false,
+        "root_volume_size": 40,
+        "data_volumes": [
+            {"device_name": "/dev/xvdb", "size": 200, "volume_type": "gp3", "iops": 3000, "throughput": 125, "encrypted": true},
+            {"device_name": "/dev/xvdc", "size": 100, "volume_type": "magnetic-legacy", "iops": null, "throughput": null}
+        ],
+        "user_data": "IyEvYmluL2Jhc2g=",
+        "scaling_policies": [
+            {"name": "cpu-target", "metric": "ASGAverageCPUUtilization", "target_value": 60.0}
+        ],
+        "cooldown_seconds": 120,
+        "scale_in_protection": false,
+        "attributes": {
+            "capacity_rebalance": true,
+            "warm_pool": {"min_size": 1}
+        }
+    }"#;
+
+    struct FixtureSource {
+        groups: Mutex<HashMap<String, LiveGroup>>,
+    }
+
+    impl FixtureSource {
+        fn new() -> Self {
+            let live: LiveGroup = serde_json::from_str(FIXTURE_ASG).unwrap();
+            let mut groups = HashMap::new();
+            groups.insert(live.identifier.clone(), live);
+            Self { groups: Mutex::new(groups) }
+        }
+
+        fn live(&self, identifier: &str) -> LiveGroup {
+            self.groups.lock().unwrap().get(identifier).cloned().unwrap()
+        }
+    }
+
+    #[async_trait]
+    impl ImportSource for FixtureSource {
+        async fn describe_group(&self, identifier: &str) -> ComputeResult<LiveGroup> {
+            self.groups
+                .lock()
+                .unwrap()
+                .get(identifier)
+                .cloned()
+                .ok_or_else(|| ComputeError::NotFound(identifier.to_string()))
+        }
+
+        async fn tag_group(&self, identifier: &str, tags: HashMap<String, String>) -> ComputeResult<()> {
+            let mut groups = self.groups.lock().unwrap();
+            let live = groups.get_mut(identifier).unwrap();
+            live.tags.extend(tags);
+            Ok(())
+        }
+
+        async fn update_group(&self, identifier: &str, group: &InstanceGroup) -> ComputeResult<()> {
+            let mut groups = self.groups.lock().unwrap();
+            let live = groups.get_mut(identifier).unwrap();
+            live.instance_type = group.instance_type.clone();
+            live.min_size = group.min_size;
+            live.max_size = group.max_size;
+            live.desired_size = group.desired_size;
+            Ok(())
+        }
+    }
+
+    fn identifier() -> String {
+        "arn:aws:autoscaling:us-east-1:123456789012:autoScalingGroup:web-asg".to_string()
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_reports_unsupported() {
+        let source = Arc::new(FixtureSource::new());
+        let store = Arc::new(InMemoryFleetStore::new());
+        let importer = FleetImporter::new(source.clone(), store.clone());
+
+        let report = importer
+            .import_fleet("web", &[identifier()], ImportOptions { fleet_id: Some("fleet-web".into()), dry_run: true })
+            .await
+            .unwrap();
+
+        assert!(report.dry_run);
+        let group = &report.fleet.instance_groups[0];
+        assert_eq!(group.instance_type, "m5.large");
+        assert_eq!(group.desired_size, 3);
+        assert_eq!(group.storage_config.root_volume_size, 40);
+        assert_eq!(group.storage_config.data_volumes.len(), 1);
+        assert_eq!(group.labels.get("team").map(String::as_str), Some("web"));
+        assert!(!group.labels.contains_key("aws:cloudformation:stack-name"));
+        assert_eq!(group.scaling_config.as_ref().unwrap().cooldown_seconds, 120);
+        assert_eq!(report.fleet.network_config.subnet_ids.len(), 2);
+
+        let mut paths: Vec<&str> = report.unsupported.iter().map(|u| u.path.as_str()).collect();
+        paths.sort();
+        assert_eq!(
+            paths,
+            vec![
+                "attributes.capacity_rebalance",
+                "attributes.warm_pool",
+                "volumes./dev/xvdb.encrypted",
+                "volumes./dev/xvdc",
+            ]
+        );
+
+        // Dry runs leave both the cloud resource and the store untouched
+        assert!(!source.live(&identifier()).tags.contains_key(MANAGED_BY_TAG));
+        assert!(store.get_fleet("fleet-web").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_import_and_update_round_trip() {
+        let source = Arc::new(FixtureSource::new());
+        let store = Arc::new(InMemoryFleetStore::new());
+        let importer = FleetImporter::new(source.clone(), store.clone());
+
+        let report = importer
+            .import_fleet("web", &[identifier()], ImportOptions { fleet_id: Some("fleet-web".into()), dry_run: false })
+            .await
+            .unwrap();
+
+        assert_eq!(
+            source.live(&identifier()).tags.get(MANAGED_BY_TAG).map(String::as_str),
+            Some(MANAGED_BY_VALUE)
+        );
+        assert!(store.get_fleet("fleet-web").await.unwrap().is_some());
+
+        // A caller that only knows the modeled fields sends an update without annotations
+        let mut desired = report.fleet.clone();
+        let group = &mut desired.instance_groups[0];
+        group.desired_size = 5;
+        group.annotations.clear();
+        desired.labels.clear();
+
+        let updated = importer.update_fleet(desired).await.unwrap();
+        let group = &updated.instance_groups[0];
+        assert_eq!(group.desired_size, 5);
+        assert_eq!(unmanaged_attributes(group).len(), 4);
+        assert_eq!(updated.labels.get(MANAGED_BY_TAG).map(String::as_str), Some(MANAGED_BY_VALUE));
+
+        let live = source.live(&identifier());
+        assert_eq!(live.desired_size, 5);
+        assert_eq!(live.attributes.len(), 2);
+        assert_eq!(live.data_volumes.len(), 2);
+
+        let err = importer
+            .import_fleet("web", &[identifier()], ImportOptions { fleet_id: Some("fleet-web".into()), dry_run: false })
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ComputeError::Conflict(_)));
+    }
+}
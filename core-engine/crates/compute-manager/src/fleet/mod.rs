@@ -1,6 +1,19 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+pub mod command;
+pub mod import;
+pub mod reconciler;
+pub mod store;
+
+pub use command::{
+    CommandBackend, CommandInvocation, CommandInvocationStore, CommandOptions, CommandRunner, FileOutputStore,
+    InMemoryCommandInvocationStore, OutputStore,
+};
+pub use import::{FleetImporter, ImportOptions, ImportReport, ImportSource, LiveGroup};
+pub use reconciler::{DriftCategory, DriftEvent, FleetReconciler, ReconcilerConfig};
+pub use store::{FleetStore, InMemoryFleetStore};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FleetConfig {
     pub id: String,
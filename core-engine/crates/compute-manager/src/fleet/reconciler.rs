@@ -0,0 +1,596 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::sync::Mutex;
+use tracing::{debug, info, warn};
+
+use sirsi_observability::monitoring::{AlertEvent, AlertManager, AlertSeverity, AlertState};
+
+use crate::error::ComputeResult;
+use super::import::{provider_identifier, ImportSource, LiveGroup, MANAGED_BY_TAG};
+use super::store::FleetStore;
+use super::{FleetConfig, InstanceGroup};
+
+pub const DRIFT_ALERT_RULE_ID: &str = "fleet-drift";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DriftCategory {
+    Capacity,
+    InstanceType,
+    Labels,
+    Network,
+}
+
+impl DriftCategory {
+    // Categories the reconciler knows how to push back without a rolling replacement
+    pub fn is_correctable(&self) -> bool {
+        matches!(self, DriftCategory::Capacity | DriftCategory::Labels)
+    }
+
+    pub fn severity(&self) -> DriftSeverity {
+        match self {
+            DriftCategory::Labels => DriftSeverity::Info,
+            DriftCategory::Capacity => DriftSeverity::Warning,
+            DriftCategory::InstanceType | DriftCategory::Network => DriftSeverity::Critical,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DriftSeverity {
+    Info,
+    Warning,
+    Critical,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DriftEvent {
+    pub id: String,
+    pub fleet_id: String,
+    pub group_id: String,
+    pub category: DriftCategory,
+    pub severity: DriftSeverity,
+    pub expected: Value,
+    pub actual: Value,
+    pub corrected: bool,
+    pub detected_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReconcilerConfig {
+    pub auto_correct: HashSet<DriftCategory>,
+    pub min_interval: Duration,
+    pub alert_threshold: DriftSeverity,
+}
+
+impl Default for ReconcilerConfig {
+    fn default() -> Self {
+        Self {
+            auto_correct: HashSet::new(),
+            min_interval: Duration::from_secs(300),
+            alert_threshold: DriftSeverity::Warning,
+        }
+    }
+}
+
+impl ReconcilerConfig {
+    pub fn with_auto_correct(mut self, category: DriftCategory) -> Self {
+        self.auto_correct.insert(category);
+        self
+    }
+
+    pub fn with_min_interval(mut self, interval: Duration) -> Self {
+        self.min_interval = interval;
+        self
+    }
+
+    pub fn with_alert_threshold(mut self, severity: DriftSeverity) -> Self {
+        self.alert_threshold = severity;
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ReconcileOutcome {
+    Reconciled(Vec<DriftEvent>),
+    RateLimited,
+    UpdateInProgress,
+}
+
+pub struct FleetReconciler {
+    source: Arc<dyn ImportSource>,
+    store: Arc<dyn FleetStore>,
+    alerts: Option<Arc<dyn AlertManager>>,
+    config: ReconcilerConfig,
+    last_run: Mutex<HashMap<String, Instant>>,
+}
+
+impl FleetReconciler {
+    pub fn new(source: Arc<dyn ImportSource>, store: Arc<dyn FleetStore>, config: ReconcilerConfig) -> Self {
+        for category in &config.auto_correct {
+            if !category.is_correctable() {
+                warn!("Auto-correct requested for {:?} drift, which is only reported", category);
+            }
+        }
+
+        Self {
+            source,
+            store,
+            alerts: None,
+            config,
+            last_run: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn with_alert_manager(mut self, alerts: Arc<dyn AlertManager>) -> Self {
+        self.alerts = Some(alerts);
+        self
+    }
+
+    pub async fn reconcile_all(&self) -> ComputeResult<Vec<(String, ReconcileOutcome)>> {
+        let mut outcomes = Vec::new();
+        for fleet in self.store.list_fleets().await? {
+            let outcome = self.reconcile(&fleet).await?;
+            outcomes.push((fleet.id, outcome));
+        }
+        Ok(outcomes)
+    }
+
+    pub async fn reconcile_fleet(&self, fleet_id: &str) -> ComputeResult<ReconcileOutcome> {
+        let fleet = self.store.get_fleet(fleet_id).await?.ok_or_else(|| {
+            crate::error::ComputeError::NotFound(format!("Fleet {} is not managed", fleet_id))
+        })?;
+        self.reconcile(&fleet).await
+    }
+
+    // Runs reconcile_all on a fixed tick until the handle is dropped or aborted
+    pub fn spawn(self: Arc<Self>, tick: Duration) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(tick);
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.reconcile_all().await {
+                    warn!("Fleet reconciliation pass failed: {}", e);
+                }
+            }
+        })
+    }
+
+    async fn reconcile(&self, fleet: &FleetConfig) -> ComputeResult<ReconcileOutcome> {
+        {
+            let mut last_run = self.last_run.lock().await;
+            if let Some(previous) = last_run.get(&fleet.id) {
+                if previous.elapsed() < self.config.min_interval {
+                    debug!("Skipping fleet {}: reconciled {:?} ago", fleet.id, previous.elapsed());
+                    return Ok(ReconcileOutcome::RateLimited);
+                }
+            }
+            last_run.insert(fleet.id.clone(), Instant::now());
+        }
+
+        let mut live_groups = Vec::with_capacity(fleet.instance_groups.len());
+        for group in &fleet.instance_groups {
+            let live = self.source.describe_group(provider_identifier(group)).await?;
+            if live.update_in_progress {
+                info!("Skipping fleet {}: rolling update in progress on {}", fleet.id, group.id);
+                return Ok(ReconcileOutcome::UpdateInProgress);
+            }
+            live_groups.push(live);
+        }
+
+        let mut events = Vec::new();
+        for (group, live) in fleet.instance_groups.iter().zip(live_groups.iter()) {
+            for mut event in detect_drift(fleet, group, live) {
+                if self.config.auto_correct.contains(&event.category) && event.category.is_correctable() {
+                    event.corrected = self.correct(group, &event).await?;
+                }
+                events.push(event);
+            }
+        }
+
+        for event in &events {
+            info!(
+                "Drift on fleet {} group {}: {:?} (corrected: {})",
+                event.fleet_id, event.group_id, event.category, event.corrected
+            );
+            if event.severity >= self.config.alert_threshold && !event.corrected {
+                self.raise_alert(event).await;
+            }
+        }
+
+        Ok(ReconcileOutcome::Reconciled(events))
+    }
+
+    async fn correct(&self, group: &InstanceGroup, event: &DriftEvent) -> ComputeResult<bool> {
+        let identifier = provider_identifier(group);
+        match event.category {
+            DriftCategory::Capacity => {
+                self.source.update_group(identifier, group).await?;
+                Ok(true)
+            }
+            DriftCategory::Labels => {
+                let tags = group
+                    .labels
+                    .iter()
+                    .filter(|(k, _)| k.as_str() != MANAGED_BY_TAG)
+                    .map(|(k, v)| (k.clone(), v.clone()))
+                    .collect();
+                self.source.tag_group(identifier, tags).await?;
+                Ok(true)
+            }
+            DriftCategory::InstanceType | DriftCategory::Network => Ok(false),
+        }
+    }
+
+    async fn raise_alert(&self, event: &DriftEvent) {
+        let Some(alerts) = &self.alerts else {
+            return;
+        };
+
+        let severity = match event.severity {
+            DriftSeverity::Critical => AlertSeverity::Critical,
+            DriftSeverity::Warning => AlertSeverity::Warning,
+            DriftSeverity::Info => AlertSeverity::Info,
+        };
+
+        let mut metadata = HashMap::new();
+        metadata.insert("fleet_id".to_string(), event.fleet_id.clone());
+        metadata.insert("group_id".to_string(), event.group_id.clone());
+        metadata.insert("category".to_string(), json!(event.category).as_str().unwrap_or_default().to_string());
+        metadata.insert("expected".to_string(), event.expected.to_string());
+        metadata.insert("actual".to_string(), event.actual.to_string());
+
+        let alert = AlertEvent {
+            id: event.id.clone(),
+            rule_id: DRIFT_ALERT_RULE_ID.to_string(),
+            severity,
+            state: AlertState::Firing,
+            message: format!(
+                "Fleet {} group {} drifted from desired {:?}",
+                event.fleet_id, event.group_id, event.category
+            ),
+            value: 1.0,
+            timestamp: event.detected_at,
+            resolved_at: None,
+            metadata,
+            actions: Vec::new(),
+        };
+
+        if let Err(e) = alerts.record_alert_event(alert).await {
+            warn!("Failed to record drift alert for fleet {}: {}", event.fleet_id, e);
+        }
+    }
+}
+
+pub fn detect_drift(fleet: &FleetConfig, group: &InstanceGroup, live: &LiveGroup) -> Vec<DriftEvent> {
+    let mut events = Vec::new();
+    let mut push = |category: DriftCategory, expected: Value, actual: Value| {
+        events.push(DriftEvent {
+            id: uuid::Uuid::new_v4().to_string(),
+            fleet_id: fleet.id.clone(),
+            group_id: group.id.clone(),
+            category,
+            severity: category.severity(),
+            expected,
+            actual,
+            corrected: false,
+            detected_at: Utc::now(),
+        });
+    };
+
+    // Desired capacity moves on its own when a scaling policy is attached
+    let track_desired = group.scaling_config.is_none();
+    if group.min_size != live.min_size
+        || group.max_size != live.max_size
+        || (track_desired && group.desired_size != live.desired_size)
+    {
+        push(
+            DriftCategory::Capacity,
+            json!({ "min": group.min_size, "max": group.max_size, "desired": group.desired_size }),
+            json!({ "min": live.min_size, "max": live.max_size, "desired": live.desired_size }),
+        );
+    }
+
+    if group.instance_type != live.instance_type {
+        push(
+            DriftCategory::InstanceType,
+            json!(group.instance_type),
+            json!(live.instance_type),
+        );
+    }
+
+    let changed: Vec<&String> = group
+        .labels
+        .iter()
+        .filter(|(k, v)| live.tags.get(*k) != Some(*v))
+        .map(|(k, _)| k)
+        .collect();
+    if !changed.is_empty() {
+        let expected: HashMap<&String, &String> = changed.iter().map(|k| (*k, &group.labels[*k])).collect();
+        let actual: HashMap<&String, Option<&String>> = changed.iter().map(|k| (*k, live.tags.get(*k))).collect();
+        push(DriftCategory::Labels, json!(expected), json!(actual));
+    }
+
+    let expected_subnets: BTreeSet<&String> = fleet.network_config.subnet_ids.iter().collect();
+    let actual_subnets: BTreeSet<&String> = live.subnet_ids.iter().collect();
+    let expected_sgs: BTreeSet<&String> = fleet.network_config.security_groups.iter().collect();
+    let actual_sgs: BTreeSet<&String> = live.security_groups.iter().collect();
+    let vpc_drift = fleet.network_config.vpc_id.is_some() && fleet.network_config.vpc_id != live.vpc_id;
+    if vpc_drift || !actual_subnets.is_subset(&expected_subnets) || !expected_sgs.is_subset(&actual_sgs) {
+        push(
+            DriftCategory::Network,
+            json!({
+                "vpc_id": fleet.network_config.vpc_id,
+                "subnet_ids": expected_subnets,
+                "security_groups": expected_sgs,
+            }),
+            json!({
+                "vpc_id": live.vpc_id,
+                "subnet_ids": actual_subnets,
+                "security_groups": actual_sgs,
+            }),
+        );
+    }
+
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fleet::import::PROVIDER_ID_ANNOTATION;
+    use crate::fleet::store::InMemoryFleetStore;
+    use async_trait::async_trait;
+    use sirsi_observability::monitoring::{AlertRule, WebhookDelivery};
+    use sirsi_observability::ObservabilityResult;
+    use std::collections::BTreeMap;
+    use std::sync::Mutex as StdMutex;
+
+    struct MockSource {
+        groups: StdMutex<HashMap<String, LiveGroup>>,
+        updates: StdMutex<Vec<String>>,
+    }
+
+    impl MockSource {
+        fn new(live: LiveGroup) -> Self {
+            let mut groups = HashMap::new();
+            groups.insert(live.identifier.clone(), live);
+            Self {
+                groups: StdMutex::new(groups),
+                updates: StdMutex::new(Vec::new()),
+            }
+        }
+
+        fn modify(&self, f: impl FnOnce(&mut LiveGroup)) {
+            let mut groups = self.groups.lock().unwrap();
+            f(groups.get_mut("asg-web").unwrap());
+        }
+    }
+
+    #[async_trait]
+    impl ImportSource for MockSource {
+        async fn describe_group(&self, identifier: &str) -> ComputeResult<LiveGroup> {
+            Ok(self.groups.lock().unwrap()[identifier].clone())
+        }
+
+        async fn tag_group(&self, identifier: &str, tags: HashMap<String, String>) -> ComputeResult<()> {
+            self.groups.lock().unwrap().get_mut(identifier).unwrap().tags.extend(tags);
+            self.updates.lock().unwrap().push(format!("tag:{}", identifier));
+            Ok(())
+        }
+
+        async fn update_group(&self, identifier: &str, group: &InstanceGroup) -> ComputeResult<()> {
+            let mut groups = self.groups.lock().unwrap();
+            let live = groups.get_mut(identifier).unwrap();
+            live.min_size = group.min_size;
+            live.max_size = group.max_size;
+            live.desired_size = group.desired_size;
+            self.updates.lock().unwrap().push(format!("update:{}", identifier));
+            Ok(())
+        }
+    }
+
+    #[derive(Default)]
+    struct RecordingAlerts {
+        events: StdMutex<Vec<AlertEvent>>,
+    }
+
+    #[async_trait]
+    impl AlertManager for RecordingAlerts {
+        async fn create_alert_rule(&self, rule: AlertRule) -> ObservabilityResult<AlertRule> {
+            Ok(rule)
+        }
+        async fn update_alert_rule(&self, rule: AlertRule) -> ObservabilityResult<AlertRule> {
+            Ok(rule)
+        }
+        async fn delete_alert_rule(&self, _id: &str) -> ObservabilityResult<()> {
+            Ok(())
+        }
+        async fn get_alert_rule(&self, id: &str) -> ObservabilityResult<AlertRule> {
+            Err(sirsi_observability::ObservabilityError::NotFound(id.to_string()))
+        }
+        async fn list_alert_rules(&self) -> ObservabilityResult<Vec<AlertRule>> {
+            Ok(Vec::new())
+        }
+        async fn get_alert_events(&self, _rule_id: Option<String>) -> ObservabilityResult<Vec<AlertEvent>> {
+            Ok(self.events.lock().unwrap().clone())
+        }
+        async fn record_alert_event(&self, event: AlertEvent) -> ObservabilityResult<AlertEvent> {
+            self.events.lock().unwrap().push(event.clone());
+            Ok(event)
+        }
+        async fn list_webhook_deliveries(&self, _channel_id: &str) -> ObservabilityResult<Vec<WebhookDelivery>> {
+            Ok(Vec::new())
+        }
+        async fn redeliver(&self, delivery_id: &str) -> ObservabilityResult<WebhookDelivery> {
+            Err(sirsi_observability::ObservabilityError::NotFound(delivery_id.to_string()))
+        }
+    }
+
+    fn live_group() -> LiveGroup {
+        let mut tags = HashMap::new();
+        tags.insert("team".to_string(), "web".to_string());
+        LiveGroup {
+            identifier: "asg-web".into(),
+            name: "web".into(),
+            instance_type: "m5.large".into(),
+            min_size: 2,
+            max_size: 6,
+            desired_size: 3,
+            tags,
+            vpc_id: Some("vpc-1".into()),
+            subnet_ids: vec!["subnet-1".into()],
+            security_groups: vec!["sg-web".into()],
+            associate_public_ip: false,
+            root_volume_size: Some(50),
+            data_volumes: Vec::new(),
+            user_data: None,
+            scaling_policies: Vec::new(),
+            cooldown_seconds: None,
+            scale_in_protection: false,
+            update_in_progress: false,
+            attributes: BTreeMap::new(),
+        }
+    }
+
+    async fn managed_store() -> Arc<InMemoryFleetStore> {
+        let mut fleet = FleetConfig::new("fleet-1".into(), "web".into());
+        fleet.network_config.vpc_id = Some("vpc-1".into());
+        fleet.network_config.subnet_ids = vec!["subnet-1".into()];
+        fleet.network_config.security_groups = vec!["sg-web".into()];
+        let mut group = InstanceGroup::new("web".into(), "web".into(), "m5.large".into()).with_scaling(2, 6, 3);
+        group.labels.insert("team".into(), "web".into());
+        group.annotations.insert(PROVIDER_ID_ANNOTATION.into(), "asg-web".into());
+        fleet.add_instance_group(group);
+
+        let store = Arc::new(InMemoryFleetStore::new());
+        store.save_fleet(&fleet).await.unwrap();
+        store
+    }
+
+    fn config() -> ReconcilerConfig {
+        ReconcilerConfig::default().with_min_interval(Duration::ZERO)
+    }
+
+    fn events(outcome: ReconcileOutcome) -> Vec<DriftEvent> {
+        match outcome {
+            ReconcileOutcome::Reconciled(events) => events,
+            other => panic!("expected reconciliation, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_no_drift() {
+        let source = Arc::new(MockSource::new(live_group()));
+        let reconciler = FleetReconciler::new(source, managed_store().await, config());
+        assert!(events(reconciler.reconcile_fleet("fleet-1").await.unwrap()).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_detect_only_mode() {
+        let source = Arc::new(MockSource::new(live_group()));
+        source.modify(|live| {
+            live.desired_size = 5;
+            live.instance_type = "m5.xlarge".into();
+        });
+        let alerts = Arc::new(RecordingAlerts::default());
+        let reconciler = FleetReconciler::new(source.clone(), managed_store().await, config())
+            .with_alert_manager(alerts.clone());
+
+        let drift = events(reconciler.reconcile_fleet("fleet-1").await.unwrap());
+        let categories: HashSet<DriftCategory> = drift.iter().map(|e| e.category).collect();
+        assert_eq!(categories, HashSet::from([DriftCategory::Capacity, DriftCategory::InstanceType]));
+        assert!(drift.iter().all(|e| !e.corrected));
+        assert!(source.updates.lock().unwrap().is_empty());
+
+        let raised = alerts.events.lock().unwrap();
+        assert_eq!(raised.len(), 2);
+        assert!(raised.iter().all(|a| a.rule_id == DRIFT_ALERT_RULE_ID));
+    }
+
+    #[tokio::test]
+    async fn test_auto_correct_opted_in_categories() {
+        let source = Arc::new(MockSource::new(live_group()));
+        source.modify(|live| {
+            live.desired_size = 5;
+            live.instance_type = "m5.xlarge".into();
+            live.tags.remove("team");
+        });
+        let alerts = Arc::new(RecordingAlerts::default());
+        let config = config()
+            .with_auto_correct(DriftCategory::Capacity)
+            .with_auto_correct(DriftCategory::Labels);
+        let reconciler = FleetReconciler::new(source.clone(), managed_store().await, config)
+            .with_alert_manager(alerts.clone());
+
+        let drift = events(reconciler.reconcile_fleet("fleet-1").await.unwrap());
+        for event in &drift {
+            assert_eq!(event.corrected, event.category != DriftCategory::InstanceType);
+        }
+        assert_eq!(source.describe_group("asg-web").await.unwrap().desired_size, 3);
+        assert_eq!(source.updates.lock().unwrap().len(), 2);
+
+        // Only the uncorrected, critical instance type drift pages
+        let raised = alerts.events.lock().unwrap();
+        assert_eq!(raised.len(), 1);
+        assert!(matches!(raised[0].severity, AlertSeverity::Critical));
+    }
+
+    #[tokio::test]
+    async fn test_skips_rolling_update_and_rate_limits() {
+        let source = Arc::new(MockSource::new(live_group()));
+        source.modify(|live| {
+            live.update_in_progress = true;
+            live.desired_size = 5;
+        });
+        let store = managed_store().await;
+        let reconciler = FleetReconciler::new(
+            source.clone(),
+            store.clone(),
+            config().with_auto_correct(DriftCategory::Capacity),
+        );
+        assert!(matches!(
+            reconciler.reconcile_fleet("fleet-1").await.unwrap(),
+            ReconcileOutcome::UpdateInProgress
+        ));
+        assert!(source.updates.lock().unwrap().is_empty());
+
+        let limited = FleetReconciler::new(
+            source,
+            store,
+            ReconcilerConfig::default().with_min_interval(Duration::from_secs(3600)),
+        );
+        assert!(matches!(
+            limited.reconcile_fleet("fleet-1").await.unwrap(),
+            ReconcileOutcome::UpdateInProgress
+        ));
+        assert!(matches!(
+            limited.reconcile_fleet("fleet-1").await.unwrap(),
+            ReconcileOutcome::RateLimited
+        ));
+    }
+
+    #[test]
+    fn test_desired_capacity_ignored_with_scaling_policy() {
+        let mut fleet = FleetConfig::new("fleet-1".into(), "web".into());
+        let mut group = InstanceGroup::new("web".into(), "web".into(), "m5.large".into()).with_scaling(2, 6, 3);
+        group.scaling_config = Some(crate::fleet::ScalingConfig {
+            metrics: Vec::new(),
+            cooldown_seconds: 60,
+            scale_in_protection: false,
+        });
+        fleet.network_config.subnet_ids = vec!["subnet-1".into()];
+        fleet.network_config.security_groups = vec!["sg-web".into()];
+        fleet.network_config.vpc_id = Some("vpc-1".into());
+        let mut live = live_group();
+        live.tags.clear();
+        live.desired_size = 6;
+        assert!(detect_drift(&fleet, &group, &live).is_empty());
+    }
+}
@@ -0,0 +1,50 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use tokio::sync::RwLock;
+
+use crate::error::ComputeResult;
+use super::FleetConfig;
+
+// Persistence for fleets under SirsiNexus management
+#[async_trait]
+pub trait FleetStore: Send + Sync {
+    async fn save_fleet(&self, fleet: &FleetConfig) -> ComputeResult<()>;
+    async fn get_fleet(&self, fleet_id: &str) -> ComputeResult<Option<FleetConfig>>;
+    async fn list_fleets(&self) -> ComputeResult<Vec<FleetConfig>>;
+    async fn delete_fleet(&self, fleet_id: &str) -> ComputeResult<()>;
+}
+
+#[derive(Default)]
+pub struct InMemoryFleetStore {
+    fleets: RwLock<HashMap<String, FleetConfig>>,
+}
+
+impl InMemoryFleetStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl FleetStore for InMemoryFleetStore {
+    async fn save_fleet(&self, fleet: &FleetConfig) -> ComputeResult<()> {
+        self.fleets.write().await.insert(fleet.id.clone(), fleet.clone());
+        Ok(())
+    }
+
+    async fn get_fleet(&self, fleet_id: &str) -> ComputeResult<Option<FleetConfig>> {
+        Ok(self.fleets.read().await.get(fleet_id).cloned())
+    }
+
+    async fn list_fleets(&self) -> ComputeResult<Vec<FleetConfig>> {
+        let mut fleets: Vec<FleetConfig> = self.fleets.read().await.values().cloned().collect();
+        fleets.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(fleets)
+    }
+
+    async fn delete_fleet(&self, fleet_id: &str) -> ComputeResult<()> {
+        self.fleets.write().await.remove(fleet_id);
+        Ok(())
+    }
+}
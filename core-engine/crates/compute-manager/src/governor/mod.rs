@@ -0,0 +1,605 @@
+// Per-account request governor. Every provider call waits here for both a concurrency slot and
+// a rate token, so a workflow fanning out hundreds of operations is paced to what the provider
+// account accepts instead of failing on API rate limits.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sirsi_observability::monitoring::{
+    AggregationType, MetricDataPoint, MetricDefinition, MetricType, MetricUnit, MetricValue,
+};
+use tokio::sync::oneshot;
+use tokio::time::Instant;
+use tracing::{debug, warn};
+
+use crate::error::{ComputeError, ComputeResult};
+
+pub mod provider;
+
+pub use provider::GovernedProvider;
+
+pub const METRICS_NAMESPACE: &str = "SirsiNexus/Compute";
+pub const QUEUE_DEPTH_METRIC: &str = "provider_queue_depth";
+pub const IN_FLIGHT_METRIC: &str = "provider_requests_in_flight";
+pub const RATE_METRIC: &str = "provider_request_rate";
+pub const THROTTLED_METRIC: &str = "provider_throttled_requests";
+pub const RETRIED_METRIC: &str = "provider_retried_requests";
+
+pub const DIM_ACCOUNT: &str = "account";
+pub const DIM_CATEGORY: &str = "category";
+
+pub const DEFAULT_TENANT: &str = "default";
+
+// Throttling responses that arrive within this long of each other count as one signal, so a
+// batch of in-flight requests failing together shrinks the rate once rather than collapsing it
+const DECREASE_COOLDOWN: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OperationCategory {
+    Read,
+    Mutating,
+}
+
+impl OperationCategory {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OperationCategory::Read => "read",
+            OperationCategory::Mutating => "mutating",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CategoryLimits {
+    pub max_concurrency: usize,
+    pub requests_per_second: f64,
+    pub burst: f64,
+}
+
+impl CategoryLimits {
+    pub fn new(max_concurrency: usize, requests_per_second: f64) -> Self {
+        Self { max_concurrency, requests_per_second, burst: requests_per_second }
+    }
+
+    pub fn with_burst(mut self, burst: f64) -> Self {
+        self.burst = burst;
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GovernorConfig {
+    pub read: CategoryLimits,
+    pub mutating: CategoryLimits,
+    pub accounts: HashMap<(String, OperationCategory), CategoryLimits>,
+    // Multiplier applied to the rate on throttling, and the fraction of the limit it stops at
+    pub decrease_factor: f64,
+    pub min_rate_fraction: f64,
+    // Fraction of the configured rate regained per second once throttling stops
+    pub recovery_per_second: f64,
+    pub max_throttle_retries: u32,
+}
+
+impl Default for GovernorConfig {
+    fn default() -> Self {
+        // Close to the default EC2 API buckets; accounts with raised quotas override these
+        Self {
+            read: CategoryLimits::new(32, 20.0).with_burst(50.0),
+            mutating: CategoryLimits::new(16, 5.0).with_burst(10.0),
+            accounts: HashMap::new(),
+            decrease_factor: 0.5,
+            min_rate_fraction: 0.05,
+            recovery_per_second: 0.1,
+            max_throttle_retries: 10,
+        }
+    }
+}
+
+impl GovernorConfig {
+    pub fn with_limits(mut self, category: OperationCategory, limits: CategoryLimits) -> Self {
+        match category {
+            OperationCategory::Read => self.read = limits,
+            OperationCategory::Mutating => self.mutating = limits,
+        }
+        self
+    }
+
+    pub fn with_account_limits(mut self, account: &str, category: OperationCategory, limits: CategoryLimits) -> Self {
+        self.accounts.insert((account.to_string(), category), limits);
+        self
+    }
+
+    pub fn with_max_throttle_retries(mut self, retries: u32) -> Self {
+        self.max_throttle_retries = retries;
+        self
+    }
+
+    pub fn limits(&self, account: &str, category: OperationCategory) -> &CategoryLimits {
+        self.accounts.get(&(account.to_string(), category)).unwrap_or(match category {
+            OperationCategory::Read => &self.read,
+            OperationCategory::Mutating => &self.mutating,
+        })
+    }
+}
+
+// Who is asking and how urgently; the account picks the limits, the tenant the fair-queue slot
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GovernedRequest {
+    pub account: String,
+    pub tenant: String,
+    pub category: OperationCategory,
+    pub priority: Priority,
+}
+
+impl GovernedRequest {
+    pub fn new(account: impl Into<String>, category: OperationCategory) -> Self {
+        Self {
+            account: account.into(),
+            tenant: DEFAULT_TENANT.to_string(),
+            category,
+            priority: Priority::Normal,
+        }
+    }
+
+    pub fn read(account: impl Into<String>) -> Self {
+        Self::new(account, OperationCategory::Read)
+    }
+
+    pub fn mutating(account: impl Into<String>) -> Self {
+        Self::new(account, OperationCategory::Mutating)
+    }
+
+    pub fn for_tenant(mut self, tenant: impl Into<String>) -> Self {
+        self.tenant = tenant.into();
+        self
+    }
+
+    pub fn with_priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LaneStats {
+    pub account: String,
+    pub category: OperationCategory,
+    pub queue_depth: usize,
+    pub in_flight: usize,
+    pub current_rate: f64,
+    pub configured_rate: f64,
+    pub completed: u64,
+    pub throttled: u64,
+    pub retried: u64,
+}
+
+// Strict priority between levels; within a level tenants take turns, one request each
+#[derive(Default)]
+struct FairQueue {
+    levels: BTreeMap<Priority, Level>,
+    len: usize,
+}
+
+#[derive(Default)]
+struct Level {
+    rotation: VecDeque<String>,
+    waiting: HashMap<String, VecDeque<oneshot::Sender<Permit>>>,
+}
+
+impl FairQueue {
+    fn push(&mut self, priority: Priority, tenant: &str, waiter: oneshot::Sender<Permit>) {
+        let level = self.levels.entry(priority).or_default();
+        let queue = level.waiting.entry(tenant.to_string()).or_default();
+        if queue.is_empty() {
+            level.rotation.push_back(tenant.to_string());
+        }
+        queue.push_back(waiter);
+        self.len += 1;
+    }
+
+    fn pop(&mut self) -> Option<oneshot::Sender<Permit>> {
+        let (&priority, level) = self.levels.iter_mut().next_back()?;
+        let tenant = level.rotation.pop_front()?;
+        let queue = level.waiting.get_mut(&tenant)?;
+        let waiter = queue.pop_front();
+        if queue.is_empty() {
+            level.waiting.remove(&tenant);
+        } else {
+            level.rotation.push_back(tenant);
+        }
+        if level.rotation.is_empty() {
+            self.levels.remove(&priority);
+        }
+        self.len -= 1;
+        waiter
+    }
+}
+
+struct LaneState {
+    limits: CategoryLimits,
+    rate: f64,
+    tokens: f64,
+    refilled_at: Instant,
+    last_decrease: Option<Instant>,
+    in_flight: usize,
+    queue: FairQueue,
+    wake_scheduled: bool,
+    completed: u64,
+    throttled: u64,
+    retried: u64,
+    reported_throttled: u64,
+    reported_retried: u64,
+}
+
+impl LaneState {
+    fn refill(&mut self, now: Instant, recovery_per_second: f64) {
+        let elapsed = now.duration_since(self.refilled_at).as_secs_f64();
+        self.refilled_at = now;
+        let configured = self.limits.requests_per_second;
+        self.rate = (self.rate + configured * recovery_per_second * elapsed).min(configured);
+        self.tokens = (self.tokens + self.rate * elapsed).min(self.limits.burst.max(1.0));
+    }
+}
+
+struct Lane {
+    account: String,
+    category: OperationCategory,
+    recovery_per_second: f64,
+    state: Mutex<LaneState>,
+}
+
+impl Lane {
+    fn new(account: &str, category: OperationCategory, limits: CategoryLimits, config: &GovernorConfig) -> Self {
+        Self {
+            account: account.to_string(),
+            category,
+            recovery_per_second: config.recovery_per_second,
+            state: Mutex::new(LaneState {
+                rate: limits.requests_per_second,
+                tokens: limits.burst.max(1.0),
+                limits,
+                refilled_at: Instant::now(),
+                last_decrease: None,
+                in_flight: 0,
+                queue: FairQueue::default(),
+                wake_scheduled: false,
+                completed: 0,
+                throttled: 0,
+                retried: 0,
+                reported_throttled: 0,
+                reported_retried: 0,
+            }),
+        }
+    }
+
+    async fn acquire(self: &Arc<Self>, priority: Priority, tenant: &str) -> ComputeResult<Permit> {
+        let (tx, rx) = oneshot::channel();
+        self.state.lock().unwrap().queue.push(priority, tenant, tx);
+        self.dispatch();
+        rx.await
+            .map_err(|_| ComputeError::Internal(format!("Request governor for {} dropped a waiter", self.account)))
+    }
+
+    // Hands out as many permits as the concurrency cap and the bucket allow, then arranges to
+    // be called again when the next token is due
+    fn dispatch(self: &Arc<Self>) {
+        let mut state = self.state.lock().unwrap();
+        state.refill(Instant::now(), self.recovery_per_second);
+        while state.in_flight < state.limits.max_concurrency && state.queue.len > 0 {
+            if state.tokens < 1.0 {
+                if !state.wake_scheduled {
+                    state.wake_scheduled = true;
+                    let delay = Duration::from_secs_f64((1.0 - state.tokens) / state.rate);
+                    let lane = self.clone();
+                    tokio::spawn(async move {
+                        tokio::time::sleep(delay).await;
+                        lane.state.lock().unwrap().wake_scheduled = false;
+                        lane.dispatch();
+                    });
+                }
+                break;
+            }
+            let Some(waiter) = state.queue.pop() else { break };
+            state.tokens -= 1.0;
+            state.in_flight += 1;
+            if let Err(mut permit) = waiter.send(Permit { lane: Some(self.clone()) }) {
+                // The caller gave up while queued
+                permit.lane = None;
+                state.tokens += 1.0;
+                state.in_flight -= 1;
+            }
+        }
+    }
+
+    fn release(self: &Arc<Self>) {
+        self.state.lock().unwrap().in_flight -= 1;
+        self.dispatch();
+    }
+
+    fn record_throttle(&self, decrease_factor: f64, min_rate_fraction: f64) {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        state.throttled += 1;
+        state.tokens = state.tokens.min(0.0);
+        if state.last_decrease.is_some_and(|at| now.duration_since(at) < DECREASE_COOLDOWN) {
+            return;
+        }
+        let floor = state.limits.requests_per_second * min_rate_fraction;
+        let previous = state.rate;
+        state.rate = (state.rate * decrease_factor).max(floor);
+        state.last_decrease = Some(now);
+        warn!(
+            "Provider throttled {} {} requests, slowing from {:.2} to {:.2} rps",
+            self.category.as_str(), self.account, previous, state.rate
+        );
+    }
+
+    fn stats(&self) -> LaneStats {
+        let mut state = self.state.lock().unwrap();
+        state.refill(Instant::now(), self.recovery_per_second);
+        LaneStats {
+            account: self.account.clone(),
+            category: self.category,
+            queue_depth: state.queue.len,
+            in_flight: state.in_flight,
+            current_rate: state.rate,
+            configured_rate: state.limits.requests_per_second,
+            completed: state.completed,
+            throttled: state.throttled,
+            retried: state.retried,
+        }
+    }
+}
+
+// Held for the duration of one provider call
+struct Permit {
+    lane: Option<Arc<Lane>>,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        if let Some(lane) = self.lane.take() {
+            lane.release();
+        }
+    }
+}
+
+pub struct RateGovernor {
+    config: GovernorConfig,
+    lanes: Mutex<HashMap<(String, OperationCategory), Arc<Lane>>>,
+}
+
+impl RateGovernor {
+    pub fn new(config: GovernorConfig) -> Self {
+        Self { config, lanes: Mutex::new(HashMap::new()) }
+    }
+
+    fn lane(&self, account: &str, category: OperationCategory) -> Arc<Lane> {
+        let mut lanes = self.lanes.lock().unwrap();
+        lanes
+            .entry((account.to_string(), category))
+            .or_insert_with(|| {
+                let limits = self.config.limits(account, category).clone();
+                Arc::new(Lane::new(account, category, limits, &self.config))
+            })
+            .clone()
+    }
+
+    // Runs `op` once a slot and a token are free. Throttled attempts were rejected before doing
+    // anything, so they are requeued behind the slowed-down bucket rather than surfaced.
+    pub async fn run<T, F, Fut>(&self, request: &GovernedRequest, mut op: F) -> ComputeResult<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = ComputeResult<T>>,
+    {
+        let lane = self.lane(&request.account, request.category);
+        let mut attempts = 0;
+        loop {
+            let permit = lane.acquire(request.priority, &request.tenant).await?;
+            let result = op().await;
+            match result {
+                Err(ComputeError::Throttled(msg)) => {
+                    lane.record_throttle(self.config.decrease_factor, self.config.min_rate_fraction);
+                    drop(permit);
+                    attempts += 1;
+                    if attempts > self.config.max_throttle_retries {
+                        return Err(ComputeError::Throttled(msg));
+                    }
+                    lane.state.lock().unwrap().retried += 1;
+                    debug!("Requeued throttled {} request for {}", request.category.as_str(), request.account);
+                }
+                result => {
+                    lane.state.lock().unwrap().completed += 1;
+                    return result;
+                }
+            }
+        }
+    }
+
+    pub fn stats(&self) -> Vec<LaneStats> {
+        let lanes: Vec<Arc<Lane>> = self.lanes.lock().unwrap().values().cloned().collect();
+        let mut stats: Vec<LaneStats> = lanes.iter().map(|lane| lane.stats()).collect();
+        stats.sort_by(|a, b| (&a.account, a.category).cmp(&(&b.account, b.category)));
+        stats
+    }
+
+    // Gauges are sampled as of `now`; throttle and retry counts cover the time since the last call
+    pub fn metric_points(&self, now: DateTime<Utc>) -> Vec<MetricDataPoint> {
+        let lanes: Vec<Arc<Lane>> = self.lanes.lock().unwrap().values().cloned().collect();
+        let mut points = Vec::new();
+        for lane in lanes {
+            let stats = lane.stats();
+            let (throttled, retried) = {
+                let mut state = lane.state.lock().unwrap();
+                let delta = (state.throttled - state.reported_throttled, state.retried - state.reported_retried);
+                state.reported_throttled = state.throttled;
+                state.reported_retried = state.retried;
+                delta
+            };
+            let dimensions = HashMap::from([
+                (DIM_ACCOUNT.to_string(), stats.account.clone()),
+                (DIM_CATEGORY.to_string(), stats.category.as_str().to_string()),
+            ]);
+            for (name, value) in [
+                (QUEUE_DEPTH_METRIC, stats.queue_depth as f64),
+                (IN_FLIGHT_METRIC, stats.in_flight as f64),
+                (RATE_METRIC, stats.current_rate),
+                (THROTTLED_METRIC, throttled as f64),
+                (RETRIED_METRIC, retried as f64),
+            ] {
+                points.push(MetricDataPoint {
+                    name: name.to_string(),
+                    namespace: METRICS_NAMESPACE.to_string(),
+                    dimensions: dimensions.clone(),
+                    timestamp: now,
+                    value: MetricValue::Single(value),
+                    exemplars: Vec::new(),
+                });
+            }
+        }
+        points
+    }
+}
+
+pub fn metric_definitions() -> Vec<MetricDefinition> {
+    let definition = |name: &str, metric_type: MetricType, aggregations: Vec<AggregationType>| MetricDefinition {
+        name: name.to_string(),
+        namespace: METRICS_NAMESPACE.to_string(),
+        metric_type,
+        unit: MetricUnit::Count,
+        dimensions: vec![DIM_ACCOUNT.to_string(), DIM_CATEGORY.to_string()],
+        aggregations,
+        retention_days: 30,
+    };
+    let gauge = || vec![AggregationType::Average, AggregationType::Maximum];
+    vec![
+        definition(QUEUE_DEPTH_METRIC, MetricType::Gauge, gauge()),
+        definition(IN_FLIGHT_METRIC, MetricType::Gauge, gauge()),
+        definition(RATE_METRIC, MetricType::Gauge, vec![AggregationType::Average, AggregationType::Minimum]),
+        definition(THROTTLED_METRIC, MetricType::Counter, vec![AggregationType::Sum]),
+        definition(RETRIED_METRIC, MetricType::Counter, vec![AggregationType::Sum]),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::Notify;
+
+    // Accepts at most `limit` calls in any rolling second and throttles the rest
+    struct ThrottlingBackend {
+        limit: usize,
+        accepted: Mutex<VecDeque<Instant>>,
+    }
+
+    impl ThrottlingBackend {
+        async fn call(&self) -> ComputeResult<()> {
+            {
+                let now = Instant::now();
+                let mut accepted = self.accepted.lock().unwrap();
+                while accepted.front().is_some_and(|at| now.duration_since(*at) >= Duration::from_secs(1)) {
+                    accepted.pop_front();
+                }
+                if accepted.len() >= self.limit {
+                    return Err(ComputeError::Throttled("Request limit exceeded".into()));
+                }
+                accepted.push_back(now);
+            }
+            tokio::time::sleep(Duration::from_millis(40)).await;
+            Ok(())
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_burst_is_paced_below_provider_limit() {
+        let config = GovernorConfig::default()
+            .with_limits(OperationCategory::Mutating, CategoryLimits::new(50, 25.0).with_burst(25.0));
+        let governor = Arc::new(RateGovernor::new(config));
+        let backend = Arc::new(ThrottlingBackend { limit: 10, accepted: Mutex::new(VecDeque::new()) });
+
+        let mut calls = Vec::new();
+        for i in 0..200 {
+            let (governor, backend) = (governor.clone(), backend.clone());
+            calls.push(tokio::spawn(async move {
+                let request = GovernedRequest::mutating("aws:123456789012").for_tenant(format!("tenant-{}", i % 4));
+                governor.run(&request, || backend.call()).await
+            }));
+        }
+        for call in calls {
+            assert!(call.await.unwrap().is_ok());
+        }
+
+        let stats = governor.stats();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].completed, 200);
+        assert_eq!((stats[0].queue_depth, stats[0].in_flight), (0, 0));
+        assert!(stats[0].throttled > 0);
+        assert_eq!(stats[0].retried, stats[0].throttled);
+        assert!(stats[0].current_rate < stats[0].configured_rate);
+
+        let points = governor.metric_points(Utc::now());
+        let throttled = points.iter().find(|p| p.name == THROTTLED_METRIC).unwrap();
+        assert!(matches!(throttled.value, MetricValue::Single(v) if v == stats[0].throttled as f64));
+        let points = governor.metric_points(Utc::now());
+        let throttled = points.iter().find(|p| p.name == THROTTLED_METRIC).unwrap();
+        assert!(matches!(throttled.value, MetricValue::Single(v) if v == 0.0));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_priority_then_tenant_round_robin() {
+        let config = GovernorConfig::default()
+            .with_limits(OperationCategory::Mutating, CategoryLimits::new(1, 1000.0).with_burst(1000.0));
+        let governor = Arc::new(RateGovernor::new(config));
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let gate = Arc::new(Notify::new());
+
+        // Holds the only slot until everything else is queued
+        let blocker = {
+            let (governor, gate) = (governor.clone(), gate.clone());
+            tokio::spawn(async move {
+                governor.run(&GovernedRequest::mutating("acct"), || async { gate.notified().await; Ok(()) }).await
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        let mut calls = Vec::new();
+        for (label, tenant, priority) in [
+            ("a-low", "a", Priority::Low),
+            ("a-1", "a", Priority::Normal),
+            ("a-2", "a", Priority::Normal),
+            ("a-3", "a", Priority::Normal),
+            ("b-1", "b", Priority::Normal),
+            ("b-high", "b", Priority::High),
+        ] {
+            let (governor, order) = (governor.clone(), order.clone());
+            calls.push(tokio::spawn(async move {
+                let request = GovernedRequest::mutating("acct").for_tenant(tenant).with_priority(priority);
+                governor.run(&request, || async { order.lock().unwrap().push(label); Ok::<_, ComputeError>(()) }).await
+            }));
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+
+        assert_eq!(governor.stats()[0].queue_depth, 6);
+        assert_eq!(governor.stats()[0].in_flight, 1);
+        gate.notify_one();
+        blocker.await.unwrap().unwrap();
+        for call in calls {
+            call.await.unwrap().unwrap();
+        }
+        assert_eq!(*order.lock().unwrap(), vec!["b-high", "a-1", "b-1", "a-2", "a-3", "a-low"]);
+    }
+}
@@ -0,0 +1,175 @@
+use std::future::Future;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::autoscaling::AutoScalingConfig;
+use crate::error::{ComputeError, ComputeResult};
+use crate::fleet::{FleetConfig, Instance, InstanceGroup};
+use crate::optimization::OptimizationStrategy;
+use crate::provider::{Provider, ProviderConfig};
+use crate::serverless::{Function, FunctionConfig};
+use super::{GovernedRequest, OperationCategory, Priority, RateGovernor, DEFAULT_TENANT};
+
+// A provider whose every call goes through the account's governor. Cheap to clone per tenant
+// or priority: `provider.for_tenant("acme").with_priority(Priority::High)`.
+#[derive(Clone)]
+pub struct GovernedProvider {
+    inner: Arc<dyn Provider>,
+    governor: Arc<RateGovernor>,
+    account: String,
+    tenant: String,
+    priority: Priority,
+}
+
+impl GovernedProvider {
+    pub fn new(inner: Arc<dyn Provider>, governor: Arc<RateGovernor>, account: impl Into<String>) -> Self {
+        Self {
+            inner,
+            governor,
+            account: account.into(),
+            tenant: DEFAULT_TENANT.to_string(),
+            priority: Priority::Normal,
+        }
+    }
+
+    pub fn for_tenant(&self, tenant: impl Into<String>) -> Self {
+        Self { tenant: tenant.into(), ..self.clone() }
+    }
+
+    pub fn with_priority(&self, priority: Priority) -> Self {
+        Self { priority, ..self.clone() }
+    }
+
+    fn request(&self, category: OperationCategory) -> GovernedRequest {
+        GovernedRequest::new(self.account.clone(), category)
+            .for_tenant(self.tenant.clone())
+            .with_priority(self.priority)
+    }
+
+    async fn read<T, F, Fut>(&self, op: F) -> ComputeResult<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = ComputeResult<T>>,
+    {
+        self.governor.run(&self.request(OperationCategory::Read), op).await
+    }
+
+    async fn mutate<T, F, Fut>(&self, op: F) -> ComputeResult<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = ComputeResult<T>>,
+    {
+        self.governor.run(&self.request(OperationCategory::Mutating), op).await
+    }
+}
+
+#[async_trait]
+impl Provider for GovernedProvider {
+    async fn init(_config: ProviderConfig) -> ComputeResult<Box<dyn Provider>> {
+        Err(ComputeError::Config("GovernedProvider wraps an initialized provider; use GovernedProvider::new".into()))
+    }
+
+    async fn create_fleet(&self, config: FleetConfig) -> ComputeResult<FleetConfig> {
+        self.mutate(|| self.inner.create_fleet(config.clone())).await
+    }
+    async fn update_fleet(&self, config: FleetConfig) -> ComputeResult<FleetConfig> {
+        self.mutate(|| self.inner.update_fleet(config.clone())).await
+    }
+    async fn delete_fleet(&self, fleet_id: &str) -> ComputeResult<()> {
+        self.mutate(|| self.inner.delete_fleet(fleet_id)).await
+    }
+    async fn get_fleet(&self, fleet_id: &str) -> ComputeResult<FleetConfig> {
+        self.read(|| self.inner.get_fleet(fleet_id)).await
+    }
+    async fn list_fleets(&self) -> ComputeResult<Vec<FleetConfig>> {
+        self.read(|| self.inner.list_fleets()).await
+    }
+
+    async fn create_instance_group(&self, fleet_id: &str, group: InstanceGroup) -> ComputeResult<InstanceGroup> {
+        self.mutate(|| self.inner.create_instance_group(fleet_id, group.clone())).await
+    }
+    async fn update_instance_group(&self, fleet_id: &str, group: InstanceGroup) -> ComputeResult<InstanceGroup> {
+        self.mutate(|| self.inner.update_instance_group(fleet_id, group.clone())).await
+    }
+    async fn delete_instance_group(&self, fleet_id: &str, group_id: &str) -> ComputeResult<()> {
+        self.mutate(|| self.inner.delete_instance_group(fleet_id, group_id)).await
+    }
+    async fn get_instance_group(&self, fleet_id: &str, group_id: &str) -> ComputeResult<InstanceGroup> {
+        self.read(|| self.inner.get_instance_group(fleet_id, group_id)).await
+    }
+    async fn list_instance_groups(&self, fleet_id: &str) -> ComputeResult<Vec<InstanceGroup>> {
+        self.read(|| self.inner.list_instance_groups(fleet_id)).await
+    }
+
+    async fn start_instance(&self, instance_id: &str) -> ComputeResult<()> {
+        self.mutate(|| self.inner.start_instance(instance_id)).await
+    }
+    async fn stop_instance(&self, instance_id: &str) -> ComputeResult<()> {
+        self.mutate(|| self.inner.stop_instance(instance_id)).await
+    }
+    async fn restart_instance(&self, instance_id: &str) -> ComputeResult<()> {
+        self.mutate(|| self.inner.restart_instance(instance_id)).await
+    }
+    async fn terminate_instance(&self, instance_id: &str) -> ComputeResult<()> {
+        self.mutate(|| self.inner.terminate_instance(instance_id)).await
+    }
+    async fn get_instance(&self, instance_id: &str) -> ComputeResult<Instance> {
+        self.read(|| self.inner.get_instance(instance_id)).await
+    }
+    async fn list_instances(&self, fleet_id: &str, group_id: Option<&str>) -> ComputeResult<Vec<Instance>> {
+        self.read(|| self.inner.list_instances(fleet_id, group_id)).await
+    }
+
+    async fn create_function(&self, config: FunctionConfig) -> ComputeResult<Function> {
+        self.mutate(|| self.inner.create_function(config.clone())).await
+    }
+    async fn update_function(&self, config: FunctionConfig) -> ComputeResult<Function> {
+        self.mutate(|| self.inner.update_function(config.clone())).await
+    }
+    async fn delete_function(&self, function_id: &str) -> ComputeResult<()> {
+        self.mutate(|| self.inner.delete_function(function_id)).await
+    }
+    async fn get_function(&self, function_id: &str) -> ComputeResult<Function> {
+        self.read(|| self.inner.get_function(function_id)).await
+    }
+    async fn list_functions(&self) -> ComputeResult<Vec<Function>> {
+        self.read(|| self.inner.list_functions()).await
+    }
+    async fn invoke_function(&self, function_id: &str, payload: Vec<u8>) -> ComputeResult<Vec<u8>> {
+        self.mutate(|| self.inner.invoke_function(function_id, payload.clone())).await
+    }
+
+    async fn configure_auto_scaling(&self, config: AutoScalingConfig) -> ComputeResult<AutoScalingConfig> {
+        self.mutate(|| self.inner.configure_auto_scaling(config.clone())).await
+    }
+    async fn update_auto_scaling(&self, config: AutoScalingConfig) -> ComputeResult<AutoScalingConfig> {
+        self.mutate(|| self.inner.update_auto_scaling(config.clone())).await
+    }
+    async fn delete_auto_scaling(&self, config_id: &str) -> ComputeResult<()> {
+        self.mutate(|| self.inner.delete_auto_scaling(config_id)).await
+    }
+    async fn get_auto_scaling(&self, config_id: &str) -> ComputeResult<AutoScalingConfig> {
+        self.read(|| self.inner.get_auto_scaling(config_id)).await
+    }
+    async fn list_auto_scaling(&self) -> ComputeResult<Vec<AutoScalingConfig>> {
+        self.read(|| self.inner.list_auto_scaling()).await
+    }
+
+    async fn analyze_resources(&self, resource_ids: Vec<String>) -> ComputeResult<Vec<OptimizationStrategy>> {
+        self.read(|| self.inner.analyze_resources(resource_ids.clone())).await
+    }
+    async fn apply_optimization(&self, strategy: OptimizationStrategy) -> ComputeResult<()> {
+        self.mutate(|| self.inner.apply_optimization(strategy.clone())).await
+    }
+    async fn get_optimization_history(&self, resource_id: &str) -> ComputeResult<Vec<OptimizationStrategy>> {
+        self.read(|| self.inner.get_optimization_history(resource_id)).await
+    }
+
+    async fn get_metrics(&self, resource_id: &str, metric_names: Vec<String>) -> ComputeResult<Vec<(String, f64)>> {
+        self.read(|| self.inner.get_metrics(resource_id, metric_names.clone())).await
+    }
+    async fn get_logs(&self, resource_id: &str, start_time: i64, end_time: i64) -> ComputeResult<Vec<String>> {
+        self.read(|| self.inner.get_logs(resource_id, start_time, end_time)).await
+    }
+}
@@ -0,0 +1,290 @@
+use std::future::Future;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use sirsi_common::{retry, ErrorKind, RetryPolicy, Retryable};
+use tonic::codegen::{Body, Bytes, StdError};
+use tonic::transport::{Channel, Endpoint};
+use tonic::{Request, Response, Status};
+
+use super::convert::from_millis;
+use super::inject_trace_context;
+use super::proto::{self, provider_service_client::ProviderServiceClient};
+use crate::autoscaling::AutoScalingConfig;
+use crate::error::{ComputeError, ComputeResult};
+use crate::fleet::{FleetConfig, Instance};
+use crate::serverless::{Function, FunctionConfig};
+
+const DEFAULT_TIMEOUT_SECS: u64 = 30;
+
+#[derive(Debug)]
+pub enum OperationStatus {
+    Running,
+    Succeeded(Box<FleetConfig>),
+    Failed(ComputeError),
+}
+
+#[derive(Debug)]
+pub struct FleetOperation {
+    pub id: String,
+    pub created_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub status: OperationStatus,
+}
+
+impl TryFrom<proto::Operation> for FleetOperation {
+    type Error = ComputeError;
+
+    fn try_from(operation: proto::Operation) -> ComputeResult<Self> {
+        let status = match (proto::OperationState::try_from(operation.state), operation.result) {
+            (Ok(proto::OperationState::Running), _) => OperationStatus::Running,
+            (Ok(proto::OperationState::Succeeded), Some(proto::operation::Result::Fleet(fleet))) => {
+                OperationStatus::Succeeded(Box::new(fleet.try_into()?))
+            }
+            (Ok(proto::OperationState::Failed), Some(proto::operation::Result::Error(error))) => {
+                OperationStatus::Failed(Status::new(tonic::Code::from(error.code), error.message).into())
+            }
+            _ => return Err(ComputeError::Internal(format!("operation {} is in an invalid state", operation.id))),
+        };
+        Ok(Self {
+            id: operation.id,
+            created_at: from_millis(operation.created_at)?,
+            completed_at: operation.completed_at.map(from_millis).transpose()?,
+            status,
+        })
+    }
+}
+
+// A call that may have run before failing can't be blindly resent; only throttling
+// guarantees the provider never acted on it
+#[derive(Debug, thiserror::Error)]
+#[error(transparent)]
+struct NotIdempotent(ComputeError);
+
+impl Retryable for NotIdempotent {
+    fn kind(&self) -> ErrorKind {
+        self.0.kind()
+    }
+
+    fn is_retryable(&self) -> bool {
+        matches!(self.0, ComputeError::Throttled(_))
+    }
+}
+
+// Typed client for a remote compute-manager. Every call carries a deadline and the
+// caller's trace context, and is retried on retryable errors.
+#[derive(Clone)]
+pub struct ProviderClient<T = Channel> {
+    inner: ProviderServiceClient<T>,
+    timeout: Duration,
+    retry: RetryPolicy,
+}
+
+impl ProviderClient<Channel> {
+    pub async fn connect(endpoint: impl Into<String>) -> ComputeResult<Self> {
+        let endpoint = Endpoint::from_shared(endpoint.into()).map_err(|e| ComputeError::Config(e.to_string()))?;
+        let channel = endpoint.connect().await.map_err(|e| ComputeError::Unavailable(e.to_string()))?;
+        Ok(Self::new(channel))
+    }
+}
+
+impl<T> ProviderClient<T>
+where
+    T: tonic::client::GrpcService<tonic::body::BoxBody> + Clone,
+    T::Error: Into<StdError>,
+    T::ResponseBody: Body<Data = Bytes> + Send + 'static,
+    <T::ResponseBody as Body>::Error: Into<StdError> + Send,
+{
+    pub fn new(transport: T) -> Self {
+        Self {
+            inner: ProviderServiceClient::new(transport),
+            timeout: Duration::from_secs(DEFAULT_TIMEOUT_SECS),
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    async fn attempt<M, R, F, Fut>(&self, message: &M, rpc: &F) -> ComputeResult<R>
+    where
+        M: Clone,
+        F: Fn(ProviderServiceClient<T>, Request<M>) -> Fut,
+        Fut: Future<Output = Result<Response<R>, Status>>,
+    {
+        let mut request = Request::new(message.clone());
+        request.set_timeout(self.timeout);
+        inject_trace_context(request.metadata_mut());
+        match tokio::time::timeout(self.timeout, rpc(self.inner.clone(), request)).await {
+            Ok(Ok(response)) => Ok(response.into_inner()),
+            Ok(Err(status)) => Err(status.into()),
+            Err(_) => Err(ComputeError::Unavailable(format!("no response within {:?}", self.timeout))),
+        }
+    }
+
+    async fn call<M, R, F, Fut>(&self, message: M, rpc: F) -> ComputeResult<R>
+    where
+        M: Clone,
+        F: Fn(ProviderServiceClient<T>, Request<M>) -> Fut,
+        Fut: Future<Output = Result<Response<R>, Status>>,
+    {
+        retry(&self.retry, || self.attempt(&message, &rpc)).await
+    }
+
+    async fn call_once<M, R, F, Fut>(&self, message: M, rpc: F) -> ComputeResult<R>
+    where
+        M: Clone,
+        F: Fn(ProviderServiceClient<T>, Request<M>) -> Fut,
+        Fut: Future<Output = Result<Response<R>, Status>>,
+    {
+        retry(&self.retry, || async { self.attempt(&message, &rpc).await.map_err(NotIdempotent) })
+            .await
+            .map_err(|e| e.0)
+    }
+
+    // Starts creating the fleet; poll the returned operation with `get_operation`
+    pub async fn create_fleet(&self, fleet: FleetConfig) -> ComputeResult<FleetOperation> {
+        let request = proto::CreateFleetRequest { fleet: Some(fleet.into()) };
+        self.call_once(request, |mut c, r| async move { c.create_fleet(r).await }).await?.try_into()
+    }
+
+    pub async fn get_operation(&self, operation_id: &str) -> ComputeResult<FleetOperation> {
+        let request = proto::GetOperationRequest { operation_id: operation_id.to_string() };
+        self.call(request, |mut c, r| async move { c.get_operation(r).await }).await?.try_into()
+    }
+
+    pub async fn wait_for_fleet(&self, operation_id: &str, poll_interval: Duration, max_wait: Duration) -> ComputeResult<FleetConfig> {
+        let deadline = tokio::time::Instant::now() + max_wait;
+        loop {
+            match self.get_operation(operation_id).await?.status {
+                OperationStatus::Succeeded(fleet) => return Ok(*fleet),
+                OperationStatus::Failed(e) => return Err(e),
+                OperationStatus::Running if tokio::time::Instant::now() >= deadline => {
+                    return Err(ComputeError::Unavailable(format!("operation {} still running after {:?}", operation_id, max_wait)));
+                }
+                OperationStatus::Running => tokio::time::sleep(poll_interval).await,
+            }
+        }
+    }
+
+    pub async fn update_fleet(&self, fleet: FleetConfig) -> ComputeResult<FleetConfig> {
+        let request = proto::UpdateFleetRequest { fleet: Some(fleet.into()) };
+        self.call(request, |mut c, r| async move { c.update_fleet(r).await }).await?.try_into()
+    }
+
+    pub async fn delete_fleet(&self, fleet_id: &str) -> ComputeResult<()> {
+        let request = proto::DeleteFleetRequest { fleet_id: fleet_id.to_string() };
+        self.call(request, |mut c, r| async move { c.delete_fleet(r).await }).await?;
+        Ok(())
+    }
+
+    pub async fn get_fleet(&self, fleet_id: &str) -> ComputeResult<FleetConfig> {
+        let request = proto::GetFleetRequest { fleet_id: fleet_id.to_string() };
+        self.call(request, |mut c, r| async move { c.get_fleet(r).await }).await?.try_into()
+    }
+
+    pub async fn list_fleets(&self) -> ComputeResult<Vec<FleetConfig>> {
+        let response = self.call(proto::ListFleetsRequest {}, |mut c, r| async move { c.list_fleets(r).await }).await?;
+        response.fleets.into_iter().map(TryInto::try_into).collect()
+    }
+
+    pub async fn start_instance(&self, instance_id: &str) -> ComputeResult<()> {
+        let request = proto::InstanceRequest { instance_id: instance_id.to_string() };
+        self.call(request, |mut c, r| async move { c.start_instance(r).await }).await?;
+        Ok(())
+    }
+
+    pub async fn stop_instance(&self, instance_id: &str) -> ComputeResult<()> {
+        let request = proto::InstanceRequest { instance_id: instance_id.to_string() };
+        self.call(request, |mut c, r| async move { c.stop_instance(r).await }).await?;
+        Ok(())
+    }
+
+    pub async fn restart_instance(&self, instance_id: &str) -> ComputeResult<()> {
+        let request = proto::InstanceRequest { instance_id: instance_id.to_string() };
+        self.call_once(request, |mut c, r| async move { c.restart_instance(r).await }).await?;
+        Ok(())
+    }
+
+    pub async fn terminate_instance(&self, instance_id: &str) -> ComputeResult<()> {
+        let request = proto::InstanceRequest { instance_id: instance_id.to_string() };
+        self.call(request, |mut c, r| async move { c.terminate_instance(r).await }).await?;
+        Ok(())
+    }
+
+    pub async fn get_instance(&self, instance_id: &str) -> ComputeResult<Instance> {
+        let request = proto::InstanceRequest { instance_id: instance_id.to_string() };
+        self.call(request, |mut c, r| async move { c.get_instance(r).await }).await?.try_into()
+    }
+
+    pub async fn list_instances(&self, fleet_id: &str, group_id: Option<&str>) -> ComputeResult<Vec<Instance>> {
+        let request = proto::ListInstancesRequest { fleet_id: fleet_id.to_string(), group_id: group_id.map(str::to_string) };
+        let response = self.call(request, |mut c, r| async move { c.list_instances(r).await }).await?;
+        response.instances.into_iter().map(TryInto::try_into).collect()
+    }
+
+    pub async fn create_function(&self, config: FunctionConfig) -> ComputeResult<Function> {
+        let request = proto::FunctionConfig::from(config);
+        self.call_once(request, |mut c, r| async move { c.create_function(r).await }).await?.try_into()
+    }
+
+    pub async fn update_function(&self, config: FunctionConfig) -> ComputeResult<Function> {
+        let request = proto::FunctionConfig::from(config);
+        self.call(request, |mut c, r| async move { c.update_function(r).await }).await?.try_into()
+    }
+
+    pub async fn delete_function(&self, function_id: &str) -> ComputeResult<()> {
+        let request = proto::FunctionRequest { function_id: function_id.to_string() };
+        self.call(request, |mut c, r| async move { c.delete_function(r).await }).await?;
+        Ok(())
+    }
+
+    pub async fn get_function(&self, function_id: &str) -> ComputeResult<Function> {
+        let request = proto::FunctionRequest { function_id: function_id.to_string() };
+        self.call(request, |mut c, r| async move { c.get_function(r).await }).await?.try_into()
+    }
+
+    pub async fn list_functions(&self) -> ComputeResult<Vec<Function>> {
+        let response = self.call(proto::ListFunctionsRequest {}, |mut c, r| async move { c.list_functions(r).await }).await?;
+        response.functions.into_iter().map(TryInto::try_into).collect()
+    }
+
+    pub async fn invoke_function(&self, function_id: &str, payload: Vec<u8>) -> ComputeResult<Vec<u8>> {
+        let request = proto::InvokeFunctionRequest { function_id: function_id.to_string(), payload };
+        let response = self.call_once(request, |mut c, r| async move { c.invoke_function(r).await }).await?;
+        Ok(response.payload)
+    }
+
+    pub async fn configure_auto_scaling(&self, config: AutoScalingConfig) -> ComputeResult<AutoScalingConfig> {
+        let request = proto::AutoScalingConfig::from(config);
+        self.call_once(request, |mut c, r| async move { c.configure_auto_scaling(r).await }).await?.try_into()
+    }
+
+    pub async fn update_auto_scaling(&self, config: AutoScalingConfig) -> ComputeResult<AutoScalingConfig> {
+        let request = proto::AutoScalingConfig::from(config);
+        self.call(request, |mut c, r| async move { c.update_auto_scaling(r).await }).await?.try_into()
+    }
+
+    pub async fn delete_auto_scaling(&self, config_id: &str) -> ComputeResult<()> {
+        let request = proto::AutoScalingRequest { config_id: config_id.to_string() };
+        self.call(request, |mut c, r| async move { c.delete_auto_scaling(r).await }).await?;
+        Ok(())
+    }
+
+    pub async fn get_auto_scaling(&self, config_id: &str) -> ComputeResult<AutoScalingConfig> {
+        let request = proto::AutoScalingRequest { config_id: config_id.to_string() };
+        self.call(request, |mut c, r| async move { c.get_auto_scaling(r).await }).await?.try_into()
+    }
+
+    pub async fn list_auto_scaling(&self) -> ComputeResult<Vec<AutoScalingConfig>> {
+        let response = self.call(proto::ListAutoScalingRequest {}, |mut c, r| async move { c.list_auto_scaling(r).await }).await?;
+        response.configs.into_iter().map(TryInto::try_into).collect()
+    }
+}
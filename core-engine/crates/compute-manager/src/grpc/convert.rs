@@ -0,0 +1,594 @@
+// Field-for-field mapping between the crate's types and the wire messages. Decoding
+// rejects unknown enum values and missing required messages rather than defaulting them.
+
+use chrono::{DateTime, Utc};
+
+use super::proto;
+use crate::autoscaling::{
+    AutoScalingConfig, ComparisonOperator, MetricStatistic, ResourceType, ScalingMetric, ScalingSchedule,
+};
+use crate::error::{ComputeError, ComputeResult};
+use crate::fleet::{
+    FileSystem, FleetConfig, Instance, InstanceGroup, InstanceMetrics, InstanceState, NetworkConfig, ScalingConfig,
+    StorageConfig, VolumeConfig, VolumeType,
+};
+use crate::fleet::ScalingMetric as GroupScalingMetric;
+use crate::serverless::{CodeRef, Function, FunctionConfig, FunctionMetrics, FunctionState, VpcConfig};
+
+fn required<T>(value: Option<T>, field: &str) -> ComputeResult<T> {
+    value.ok_or_else(|| ComputeError::Validation(format!("{} is required", field)))
+}
+
+fn unknown(field: &str, value: i32) -> ComputeError {
+    ComputeError::Validation(format!("unknown {} {}", field, value))
+}
+
+pub(crate) fn to_millis(time: DateTime<Utc>) -> i64 {
+    time.timestamp_millis()
+}
+
+pub(crate) fn from_millis(millis: i64) -> ComputeResult<DateTime<Utc>> {
+    DateTime::from_timestamp_millis(millis).ok_or_else(|| ComputeError::Validation(format!("timestamp {} out of range", millis)))
+}
+
+impl From<FleetConfig> for proto::FleetConfig {
+    fn from(fleet: FleetConfig) -> Self {
+        Self {
+            id: fleet.id,
+            name: fleet.name,
+            instance_groups: fleet.instance_groups.into_iter().map(Into::into).collect(),
+            network_config: Some(fleet.network_config.into()),
+            labels: fleet.labels,
+            annotations: fleet.annotations,
+        }
+    }
+}
+
+impl TryFrom<proto::FleetConfig> for FleetConfig {
+    type Error = ComputeError;
+
+    fn try_from(fleet: proto::FleetConfig) -> ComputeResult<Self> {
+        Ok(Self {
+            id: fleet.id,
+            name: fleet.name,
+            instance_groups: fleet.instance_groups.into_iter().map(TryInto::try_into).collect::<ComputeResult<_>>()?,
+            network_config: required(fleet.network_config, "network_config")?.into(),
+            labels: fleet.labels,
+            annotations: fleet.annotations,
+        })
+    }
+}
+
+impl From<NetworkConfig> for proto::NetworkConfig {
+    fn from(network: NetworkConfig) -> Self {
+        Self {
+            vpc_id: network.vpc_id,
+            subnet_ids: network.subnet_ids,
+            security_groups: network.security_groups,
+            enable_public_ip: network.enable_public_ip,
+            dns_zones: network.dns_zones,
+        }
+    }
+}
+
+impl From<proto::NetworkConfig> for NetworkConfig {
+    fn from(network: proto::NetworkConfig) -> Self {
+        Self {
+            vpc_id: network.vpc_id,
+            subnet_ids: network.subnet_ids,
+            security_groups: network.security_groups,
+            enable_public_ip: network.enable_public_ip,
+            dns_zones: network.dns_zones,
+        }
+    }
+}
+
+impl From<InstanceGroup> for proto::InstanceGroup {
+    fn from(group: InstanceGroup) -> Self {
+        Self {
+            id: group.id,
+            name: group.name,
+            instance_type: group.instance_type,
+            min_size: group.min_size,
+            max_size: group.max_size,
+            desired_size: group.desired_size,
+            labels: group.labels,
+            annotations: group.annotations,
+            startup_script: group.startup_script,
+            storage_config: Some(group.storage_config.into()),
+            scaling_config: group.scaling_config.map(Into::into),
+        }
+    }
+}
+
+impl TryFrom<proto::InstanceGroup> for InstanceGroup {
+    type Error = ComputeError;
+
+    fn try_from(group: proto::InstanceGroup) -> ComputeResult<Self> {
+        Ok(Self {
+            id: group.id,
+            name: group.name,
+            instance_type: group.instance_type,
+            min_size: group.min_size,
+            max_size: group.max_size,
+            desired_size: group.desired_size,
+            labels: group.labels,
+            annotations: group.annotations,
+            startup_script: group.startup_script,
+            storage_config: required(group.storage_config, "storage_config")?.try_into()?,
+            scaling_config: group.scaling_config.map(Into::into),
+        })
+    }
+}
+
+impl From<StorageConfig> for proto::StorageConfig {
+    fn from(storage: StorageConfig) -> Self {
+        Self {
+            root_volume_size: storage.root_volume_size,
+            data_volumes: storage.data_volumes.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl TryFrom<proto::StorageConfig> for StorageConfig {
+    type Error = ComputeError;
+
+    fn try_from(storage: proto::StorageConfig) -> ComputeResult<Self> {
+        Ok(Self {
+            root_volume_size: storage.root_volume_size,
+            data_volumes: storage.data_volumes.into_iter().map(TryInto::try_into).collect::<ComputeResult<_>>()?,
+        })
+    }
+}
+
+impl From<VolumeConfig> for proto::VolumeConfig {
+    fn from(volume: VolumeConfig) -> Self {
+        let volume_type = match volume.volume_type {
+            VolumeType::Gp2 => proto::VolumeType::Gp2,
+            VolumeType::Gp3 => proto::VolumeType::Gp3,
+            VolumeType::Io1 => proto::VolumeType::Io1,
+            VolumeType::Io2 => proto::VolumeType::Io2,
+            VolumeType::St1 => proto::VolumeType::St1,
+            VolumeType::Sc1 => proto::VolumeType::Sc1,
+            VolumeType::Standard => proto::VolumeType::Standard,
+        };
+        let file_system = match volume.file_system {
+            FileSystem::Ext4 => proto::FileSystem::Ext4,
+            FileSystem::Xfs => proto::FileSystem::Xfs,
+            FileSystem::Ntfs => proto::FileSystem::Ntfs,
+        };
+        Self {
+            size: volume.size,
+            volume_type: volume_type as i32,
+            iops: volume.iops,
+            throughput: volume.throughput,
+            mount_path: volume.mount_path,
+            file_system: file_system as i32,
+        }
+    }
+}
+
+impl TryFrom<proto::VolumeConfig> for VolumeConfig {
+    type Error = ComputeError;
+
+    fn try_from(volume: proto::VolumeConfig) -> ComputeResult<Self> {
+        let volume_type = match proto::VolumeType::try_from(volume.volume_type) {
+            Ok(proto::VolumeType::Gp2) => VolumeType::Gp2,
+            Ok(proto::VolumeType::Gp3) => VolumeType::Gp3,
+            Ok(proto::VolumeType::Io1) => VolumeType::Io1,
+            Ok(proto::VolumeType::Io2) => VolumeType::Io2,
+            Ok(proto::VolumeType::St1) => VolumeType::St1,
+            Ok(proto::VolumeType::Sc1) => VolumeType::Sc1,
+            Ok(proto::VolumeType::Standard) => VolumeType::Standard,
+            _ => return Err(unknown("volume_type", volume.volume_type)),
+        };
+        let file_system = match proto::FileSystem::try_from(volume.file_system) {
+            Ok(proto::FileSystem::Ext4) => FileSystem::Ext4,
+            Ok(proto::FileSystem::Xfs) => FileSystem::Xfs,
+            Ok(proto::FileSystem::Ntfs) => FileSystem::Ntfs,
+            _ => return Err(unknown("file_system", volume.file_system)),
+        };
+        Ok(Self {
+            size: volume.size,
+            volume_type,
+            iops: volume.iops,
+            throughput: volume.throughput,
+            mount_path: volume.mount_path,
+            file_system,
+        })
+    }
+}
+
+impl From<ScalingConfig> for proto::ScalingConfig {
+    fn from(scaling: ScalingConfig) -> Self {
+        Self {
+            metrics: scaling
+                .metrics
+                .into_iter()
+                .map(|m| proto::GroupScalingMetric {
+                    name: m.name,
+                    target_value: m.target_value,
+                    scale_out_threshold: m.scale_out_threshold,
+                    scale_in_threshold: m.scale_in_threshold,
+                    evaluation_periods: m.evaluation_periods,
+                })
+                .collect(),
+            cooldown_seconds: scaling.cooldown_seconds,
+            scale_in_protection: scaling.scale_in_protection,
+        }
+    }
+}
+
+impl From<proto::ScalingConfig> for ScalingConfig {
+    fn from(scaling: proto::ScalingConfig) -> Self {
+        Self {
+            metrics: scaling
+                .metrics
+                .into_iter()
+                .map(|m| GroupScalingMetric {
+                    name: m.name,
+                    target_value: m.target_value,
+                    scale_out_threshold: m.scale_out_threshold,
+                    scale_in_threshold: m.scale_in_threshold,
+                    evaluation_periods: m.evaluation_periods,
+                })
+                .collect(),
+            cooldown_seconds: scaling.cooldown_seconds,
+            scale_in_protection: scaling.scale_in_protection,
+        }
+    }
+}
+
+impl From<Instance> for proto::Instance {
+    fn from(instance: Instance) -> Self {
+        let state = match instance.state {
+            InstanceState::Pending => proto::InstanceState::Pending,
+            InstanceState::Running => proto::InstanceState::Running,
+            InstanceState::Stopping => proto::InstanceState::Stopping,
+            InstanceState::Stopped => proto::InstanceState::Stopped,
+            InstanceState::ShuttingDown => proto::InstanceState::ShuttingDown,
+            InstanceState::Terminated => proto::InstanceState::Terminated,
+        };
+        Self {
+            instance_id: instance.instance_id,
+            group_id: instance.group_id,
+            fleet_id: instance.fleet_id,
+            instance_type: instance.instance_type,
+            private_ip: instance.private_ip,
+            public_ip: instance.public_ip,
+            state: state as i32,
+            launch_time: to_millis(instance.launch_time),
+            labels: instance.labels,
+            metrics: instance.metrics.map(|m| proto::InstanceMetrics {
+                cpu_utilization: m.cpu_utilization,
+                memory_utilization: m.memory_utilization,
+                network_in_bytes: m.network_in_bytes,
+                network_out_bytes: m.network_out_bytes,
+                disk_read_ops: m.disk_read_ops,
+                disk_write_ops: m.disk_write_ops,
+                disk_read_bytes: m.disk_read_bytes,
+                disk_write_bytes: m.disk_write_bytes,
+            }),
+        }
+    }
+}
+
+impl TryFrom<proto::Instance> for Instance {
+    type Error = ComputeError;
+
+    fn try_from(instance: proto::Instance) -> ComputeResult<Self> {
+        let state = match proto::InstanceState::try_from(instance.state) {
+            Ok(proto::InstanceState::Pending) => InstanceState::Pending,
+            Ok(proto::InstanceState::Running) => InstanceState::Running,
+            Ok(proto::InstanceState::Stopping) => InstanceState::Stopping,
+            Ok(proto::InstanceState::Stopped) => InstanceState::Stopped,
+            Ok(proto::InstanceState::ShuttingDown) => InstanceState::ShuttingDown,
+            Ok(proto::InstanceState::Terminated) => InstanceState::Terminated,
+            _ => return Err(unknown("instance state", instance.state)),
+        };
+        Ok(Self {
+            instance_id: instance.instance_id,
+            group_id: instance.group_id,
+            fleet_id: instance.fleet_id,
+            instance_type: instance.instance_type,
+            private_ip: instance.private_ip,
+            public_ip: instance.public_ip,
+            state,
+            launch_time: from_millis(instance.launch_time)?,
+            labels: instance.labels,
+            metrics: instance.metrics.map(|m| InstanceMetrics {
+                cpu_utilization: m.cpu_utilization,
+                memory_utilization: m.memory_utilization,
+                network_in_bytes: m.network_in_bytes,
+                network_out_bytes: m.network_out_bytes,
+                disk_read_ops: m.disk_read_ops,
+                disk_write_ops: m.disk_write_ops,
+                disk_read_bytes: m.disk_read_bytes,
+                disk_write_bytes: m.disk_write_bytes,
+            }),
+        })
+    }
+}
+
+impl From<VpcConfig> for proto::VpcConfig {
+    fn from(vpc: VpcConfig) -> Self {
+        Self { subnet_ids: vpc.subnet_ids, security_group_ids: vpc.security_group_ids }
+    }
+}
+
+impl From<proto::VpcConfig> for VpcConfig {
+    fn from(vpc: proto::VpcConfig) -> Self {
+        Self { subnet_ids: vpc.subnet_ids, security_group_ids: vpc.security_group_ids }
+    }
+}
+
+impl From<FunctionConfig> for proto::FunctionConfig {
+    fn from(config: FunctionConfig) -> Self {
+        Self {
+            name: config.name,
+            runtime: config.runtime,
+            handler: config.handler,
+            code: config.code,
+            code_ref: config.code_ref.map(|c| proto::CodeRef {
+                digest: c.digest,
+                location: c.location,
+                size_bytes: c.size_bytes,
+            }),
+            description: config.description,
+            memory_mb: config.memory_mb,
+            timeout_sec: config.timeout_sec,
+            environment: config.environment,
+            labels: config.labels,
+            annotations: config.annotations,
+            vpc_config: config.vpc_config.map(Into::into),
+            provisioned_concurrency: config.provisioned_concurrency,
+        }
+    }
+}
+
+impl From<proto::FunctionConfig> for FunctionConfig {
+    fn from(config: proto::FunctionConfig) -> Self {
+        Self {
+            name: config.name,
+            runtime: config.runtime,
+            handler: config.handler,
+            code: config.code,
+            code_ref: config.code_ref.map(|c| CodeRef {
+                digest: c.digest,
+                location: c.location,
+                size_bytes: c.size_bytes,
+            }),
+            description: config.description,
+            memory_mb: config.memory_mb,
+            timeout_sec: config.timeout_sec,
+            environment: config.environment,
+            labels: config.labels,
+            annotations: config.annotations,
+            vpc_config: config.vpc_config.map(Into::into),
+            provisioned_concurrency: config.provisioned_concurrency,
+        }
+    }
+}
+
+impl From<Function> for proto::Function {
+    fn from(function: Function) -> Self {
+        let state = match function.state {
+            FunctionState::Pending => proto::FunctionState::Pending,
+            FunctionState::Active => proto::FunctionState::Active,
+            FunctionState::Inactive => proto::FunctionState::Inactive,
+            FunctionState::Failed => proto::FunctionState::Failed,
+        };
+        Self {
+            name: function.name,
+            runtime: function.runtime,
+            handler: function.handler,
+            description: function.description,
+            memory_mb: function.memory_mb,
+            timeout_sec: function.timeout_sec,
+            environment: function.environment,
+            labels: function.labels,
+            annotations: function.annotations,
+            state: state as i32,
+            metrics: function.metrics.map(|m| proto::FunctionMetrics {
+                invocations: m.invocations,
+                errors: m.errors,
+                throttles: m.throttles,
+                duration_ms: m.duration_ms,
+                concurrent_executions: m.concurrent_executions,
+                memory_utilization: m.memory_utilization,
+                cold_start_rate: m.cold_start_rate,
+                p95_init_duration_ms: m.p95_init_duration_ms,
+            }),
+            vpc_config: function.vpc_config.map(Into::into),
+        }
+    }
+}
+
+impl TryFrom<proto::Function> for Function {
+    type Error = ComputeError;
+
+    fn try_from(function: proto::Function) -> ComputeResult<Self> {
+        let state = match proto::FunctionState::try_from(function.state) {
+            Ok(proto::FunctionState::Pending) => FunctionState::Pending,
+            Ok(proto::FunctionState::Active) => FunctionState::Active,
+            Ok(proto::FunctionState::Inactive) => FunctionState::Inactive,
+            Ok(proto::FunctionState::Failed) => FunctionState::Failed,
+            _ => return Err(unknown("function state", function.state)),
+        };
+        Ok(Self {
+            name: function.name,
+            runtime: function.runtime,
+            handler: function.handler,
+            description: function.description,
+            memory_mb: function.memory_mb,
+            timeout_sec: function.timeout_sec,
+            environment: function.environment,
+            labels: function.labels,
+            annotations: function.annotations,
+            state,
+            metrics: function.metrics.map(|m| FunctionMetrics {
+                invocations: m.invocations,
+                errors: m.errors,
+                throttles: m.throttles,
+                duration_ms: m.duration_ms,
+                concurrent_executions: m.concurrent_executions,
+                memory_utilization: m.memory_utilization,
+                cold_start_rate: m.cold_start_rate,
+                p95_init_duration_ms: m.p95_init_duration_ms,
+            }),
+            vpc_config: function.vpc_config.map(Into::into),
+        })
+    }
+}
+
+impl From<AutoScalingConfig> for proto::AutoScalingConfig {
+    fn from(config: AutoScalingConfig) -> Self {
+        let resource_type = match config.resource_type {
+            ResourceType::InstanceGroup => proto::ScalableResourceType::InstanceGroup,
+            ResourceType::Function => proto::ScalableResourceType::Function,
+            ResourceType::Database => proto::ScalableResourceType::Database,
+            ResourceType::Cache => proto::ScalableResourceType::Cache,
+            ResourceType::MlDeployment => proto::ScalableResourceType::MlDeployment,
+        };
+        Self {
+            id: config.id,
+            name: config.name,
+            resource_id: config.resource_id,
+            resource_type: resource_type as i32,
+            min_capacity: config.min_capacity,
+            max_capacity: config.max_capacity,
+            desired_capacity: config.desired_capacity,
+            cooldown_seconds: config.cooldown_seconds,
+            metrics: config.metrics.into_iter().map(Into::into).collect(),
+            schedules: config.schedules.into_iter().map(Into::into).collect(),
+            labels: config.labels,
+            annotations: config.annotations,
+        }
+    }
+}
+
+impl TryFrom<proto::AutoScalingConfig> for AutoScalingConfig {
+    type Error = ComputeError;
+
+    fn try_from(config: proto::AutoScalingConfig) -> ComputeResult<Self> {
+        let resource_type = match proto::ScalableResourceType::try_from(config.resource_type) {
+            Ok(proto::ScalableResourceType::InstanceGroup) => ResourceType::InstanceGroup,
+            Ok(proto::ScalableResourceType::Function) => ResourceType::Function,
+            Ok(proto::ScalableResourceType::Database) => ResourceType::Database,
+            Ok(proto::ScalableResourceType::Cache) => ResourceType::Cache,
+            Ok(proto::ScalableResourceType::MlDeployment) => ResourceType::MlDeployment,
+            _ => return Err(unknown("resource_type", config.resource_type)),
+        };
+        Ok(Self {
+            id: config.id,
+            name: config.name,
+            resource_id: config.resource_id,
+            resource_type,
+            min_capacity: config.min_capacity,
+            max_capacity: config.max_capacity,
+            desired_capacity: config.desired_capacity,
+            cooldown_seconds: config.cooldown_seconds,
+            metrics: config.metrics.into_iter().map(TryInto::try_into).collect::<ComputeResult<_>>()?,
+            schedules: config.schedules.into_iter().map(TryInto::try_into).collect::<ComputeResult<_>>()?,
+            labels: config.labels,
+            annotations: config.annotations,
+        })
+    }
+}
+
+impl From<ScalingMetric> for proto::ScalingMetric {
+    fn from(metric: ScalingMetric) -> Self {
+        let statistic = match metric.statistic {
+            MetricStatistic::Average => (proto::StatisticKind::Average, 0.0),
+            MetricStatistic::Sum => (proto::StatisticKind::Sum, 0.0),
+            MetricStatistic::Minimum => (proto::StatisticKind::Minimum, 0.0),
+            MetricStatistic::Maximum => (proto::StatisticKind::Maximum, 0.0),
+            MetricStatistic::SampleCount => (proto::StatisticKind::SampleCount, 0.0),
+            MetricStatistic::Percentile(p) => (proto::StatisticKind::Percentile, p),
+        };
+        let comparison = match metric.comparison {
+            ComparisonOperator::GreaterThanThreshold => proto::ComparisonOperator::GreaterThanThreshold,
+            ComparisonOperator::GreaterThanOrEqualToThreshold => proto::ComparisonOperator::GreaterThanOrEqualToThreshold,
+            ComparisonOperator::LessThanThreshold => proto::ComparisonOperator::LessThanThreshold,
+            ComparisonOperator::LessThanOrEqualToThreshold => proto::ComparisonOperator::LessThanOrEqualToThreshold,
+        };
+        Self {
+            name: metric.name,
+            namespace: metric.namespace,
+            dimensions: metric.dimensions,
+            statistic: Some(proto::MetricStatistic { kind: statistic.0 as i32, percentile: statistic.1 }),
+            comparison: comparison as i32,
+            threshold: metric.threshold,
+            period_seconds: metric.period_seconds,
+            evaluation_periods: metric.evaluation_periods,
+            datapoints_to_alarm: metric.datapoints_to_alarm,
+            scale_adjustment: metric.scale_adjustment,
+        }
+    }
+}
+
+impl TryFrom<proto::ScalingMetric> for ScalingMetric {
+    type Error = ComputeError;
+
+    fn try_from(metric: proto::ScalingMetric) -> ComputeResult<Self> {
+        let statistic = required(metric.statistic, "statistic")?;
+        let statistic = match proto::StatisticKind::try_from(statistic.kind) {
+            Ok(proto::StatisticKind::Average) => MetricStatistic::Average,
+            Ok(proto::StatisticKind::Sum) => MetricStatistic::Sum,
+            Ok(proto::StatisticKind::Minimum) => MetricStatistic::Minimum,
+            Ok(proto::StatisticKind::Maximum) => MetricStatistic::Maximum,
+            Ok(proto::StatisticKind::SampleCount) => MetricStatistic::SampleCount,
+            Ok(proto::StatisticKind::Percentile) => MetricStatistic::Percentile(statistic.percentile),
+            _ => return Err(unknown("statistic", statistic.kind)),
+        };
+        let comparison = match proto::ComparisonOperator::try_from(metric.comparison) {
+            Ok(proto::ComparisonOperator::GreaterThanThreshold) => ComparisonOperator::GreaterThanThreshold,
+            Ok(proto::ComparisonOperator::GreaterThanOrEqualToThreshold) => ComparisonOperator::GreaterThanOrEqualToThreshold,
+            Ok(proto::ComparisonOperator::LessThanThreshold) => ComparisonOperator::LessThanThreshold,
+            Ok(proto::ComparisonOperator::LessThanOrEqualToThreshold) => ComparisonOperator::LessThanOrEqualToThreshold,
+            _ => return Err(unknown("comparison", metric.comparison)),
+        };
+        Ok(Self {
+            name: metric.name,
+            namespace: metric.namespace,
+            dimensions: metric.dimensions,
+            statistic,
+            comparison,
+            threshold: metric.threshold,
+            period_seconds: metric.period_seconds,
+            evaluation_periods: metric.evaluation_periods,
+            datapoints_to_alarm: metric.datapoints_to_alarm,
+            scale_adjustment: metric.scale_adjustment,
+        })
+    }
+}
+
+impl From<ScalingSchedule> for proto::ScalingSchedule {
+    fn from(schedule: ScalingSchedule) -> Self {
+        Self {
+            name: schedule.name,
+            recurrence: schedule.recurrence,
+            min_capacity: schedule.min_capacity,
+            max_capacity: schedule.max_capacity,
+            desired_capacity: schedule.desired_capacity,
+            start_time: schedule.start_time.map(to_millis),
+            end_time: schedule.end_time.map(to_millis),
+        }
+    }
+}
+
+impl TryFrom<proto::ScalingSchedule> for ScalingSchedule {
+    type Error = ComputeError;
+
+    fn try_from(schedule: proto::ScalingSchedule) -> ComputeResult<Self> {
+        Ok(Self {
+            name: schedule.name,
+            recurrence: schedule.recurrence,
+            min_capacity: schedule.min_capacity,
+            max_capacity: schedule.max_capacity,
+            desired_capacity: schedule.desired_capacity,
+            start_time: schedule.start_time.map(from_millis).transpose()?,
+            end_time: schedule.end_time.map(from_millis).transpose()?,
+        })
+    }
+}
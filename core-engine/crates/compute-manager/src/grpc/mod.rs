@@ -0,0 +1,356 @@
+// Remote access to a compute `Provider` over gRPC, for services that would rather call
+// compute-manager than link it
+
+use opentelemetry::global;
+use opentelemetry::propagation::{Extractor, Injector};
+use opentelemetry::Context;
+use tonic::metadata::{MetadataKey, MetadataMap, MetadataValue};
+
+pub mod client;
+pub mod convert;
+pub mod server;
+
+pub use client::{FleetOperation, OperationStatus, ProviderClient};
+pub use server::ProviderGrpcServer;
+
+#[allow(clippy::large_enum_variant)]
+pub mod proto {
+    tonic::include_proto!("sirsi.compute.provider.v1");
+}
+
+struct MetadataInjector<'a>(&'a mut MetadataMap);
+
+impl Injector for MetadataInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        if let (Ok(key), Ok(value)) = (MetadataKey::from_bytes(key.as_bytes()), MetadataValue::try_from(value)) {
+            self.0.insert(key, value);
+        }
+    }
+}
+
+struct MetadataExtractor<'a>(&'a MetadataMap);
+
+impl Extractor for MetadataExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0
+            .keys()
+            .filter_map(|key| match key {
+                tonic::metadata::KeyRef::Ascii(key) => Some(key.as_str()),
+                tonic::metadata::KeyRef::Binary(_) => None,
+            })
+            .collect()
+    }
+}
+
+// Carries the caller's trace (`traceparent` with the W3C propagator) in request metadata
+pub(crate) fn inject_trace_context(metadata: &mut MetadataMap) {
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&Context::current(), &mut MetadataInjector(metadata))
+    });
+}
+
+pub(crate) fn extract_trace_context(metadata: &MetadataMap) -> Context {
+    global::get_text_map_propagator(|propagator| propagator.extract(&MetadataExtractor(metadata)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, VecDeque};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use async_trait::async_trait;
+    use chrono::{TimeZone, Utc};
+    use serde_json::json;
+    use sirsi_common::RetryPolicy;
+
+    use super::*;
+    use crate::autoscaling::{AutoScalingConfig, ComparisonOperator, MetricStatistic, ResourceType, ScalingMetric, ScalingSchedule};
+    use crate::error::{ComputeError, ComputeResult};
+    use crate::fleet::{
+        FileSystem, FleetConfig, Instance, InstanceGroup, InstanceMetrics, InstanceState, ScalingConfig, VolumeConfig, VolumeType,
+    };
+    use crate::optimization::OptimizationStrategy;
+    use crate::provider::{Provider, ProviderConfig};
+    use crate::serverless::{CodeRef, Function, FunctionConfig, FunctionState, VpcConfig};
+
+    // Records what reached it so tests can compare against what the client sent
+    #[derive(Default)]
+    struct FakeProvider {
+        fleets: Mutex<HashMap<String, FleetConfig>>,
+        functions: Mutex<HashMap<String, FunctionConfig>>,
+        scaling: Mutex<HashMap<String, AutoScalingConfig>>,
+        calls: Mutex<Vec<String>>,
+        failures: Mutex<HashMap<&'static str, VecDeque<ComputeError>>>,
+    }
+
+    impl FakeProvider {
+        fn fail(&self, method: &'static str, error: ComputeError) {
+            self.failures.lock().unwrap().entry(method).or_default().push_back(error);
+        }
+
+        fn called(&self, method: &'static str) -> ComputeResult<()> {
+            self.calls.lock().unwrap().push(method.to_string());
+            match self.failures.lock().unwrap().get_mut(method).and_then(VecDeque::pop_front) {
+                Some(error) => Err(error),
+                None => Ok(()),
+            }
+        }
+
+        fn calls(&self, method: &str) -> usize {
+            self.calls.lock().unwrap().iter().filter(|c| *c == method).count()
+        }
+    }
+
+    fn unsupported<T>() -> ComputeResult<T> {
+        Err(ComputeError::Config("not supported by the fake provider".into()))
+    }
+
+    fn instance(fleet_id: &str, group_id: &str) -> Instance {
+        Instance {
+            instance_id: "i-1".into(),
+            group_id: group_id.into(),
+            fleet_id: fleet_id.into(),
+            instance_type: "m5.large".into(),
+            private_ip: "10.0.0.4".into(),
+            public_ip: None,
+            state: InstanceState::ShuttingDown,
+            launch_time: Utc.with_ymd_and_hms(2026, 9, 1, 12, 30, 0).unwrap(),
+            labels: HashMap::from([("team".to_string(), "core".to_string())]),
+            metrics: Some(InstanceMetrics {
+                cpu_utilization: 41.5,
+                memory_utilization: 63.25,
+                network_in_bytes: 1 << 40,
+                network_out_bytes: 7,
+                disk_read_ops: 3,
+                disk_write_ops: 4,
+                disk_read_bytes: 5,
+                disk_write_bytes: 6,
+            }),
+        }
+    }
+
+    #[async_trait]
+    impl Provider for FakeProvider {
+        async fn init(_config: ProviderConfig) -> ComputeResult<Box<dyn Provider>> {
+            Ok(Box::new(FakeProvider::default()))
+        }
+
+        async fn create_fleet(&self, config: FleetConfig) -> ComputeResult<FleetConfig> {
+            self.called("create_fleet")?;
+            self.fleets.lock().unwrap().insert(config.id.clone(), config.clone());
+            Ok(config)
+        }
+        async fn update_fleet(&self, config: FleetConfig) -> ComputeResult<FleetConfig> {
+            self.create_fleet(config).await
+        }
+        async fn delete_fleet(&self, fleet_id: &str) -> ComputeResult<()> {
+            self.called("delete_fleet")?;
+            self.fleets.lock().unwrap().remove(fleet_id);
+            Ok(())
+        }
+        async fn get_fleet(&self, fleet_id: &str) -> ComputeResult<FleetConfig> {
+            self.called("get_fleet")?;
+            let fleet = self.fleets.lock().unwrap().get(fleet_id).cloned();
+            fleet.ok_or_else(|| ComputeError::NotFound(format!("fleet {}", fleet_id)))
+        }
+        async fn list_fleets(&self) -> ComputeResult<Vec<FleetConfig>> {
+            Ok(self.fleets.lock().unwrap().values().cloned().collect())
+        }
+
+        async fn create_instance_group(&self, _: &str, _: InstanceGroup) -> ComputeResult<InstanceGroup> { unsupported() }
+        async fn update_instance_group(&self, _: &str, _: InstanceGroup) -> ComputeResult<InstanceGroup> { unsupported() }
+        async fn delete_instance_group(&self, _: &str, _: &str) -> ComputeResult<()> { unsupported() }
+        async fn get_instance_group(&self, _: &str, _: &str) -> ComputeResult<InstanceGroup> { unsupported() }
+        async fn list_instance_groups(&self, _: &str) -> ComputeResult<Vec<InstanceGroup>> { unsupported() }
+
+        async fn start_instance(&self, _: &str) -> ComputeResult<()> { self.called("start_instance") }
+        async fn stop_instance(&self, _: &str) -> ComputeResult<()> { self.called("stop_instance") }
+        async fn restart_instance(&self, _: &str) -> ComputeResult<()> { self.called("restart_instance") }
+        async fn terminate_instance(&self, _: &str) -> ComputeResult<()> { self.called("terminate_instance") }
+        async fn get_instance(&self, _: &str) -> ComputeResult<Instance> {
+            self.called("get_instance")?;
+            Ok(instance("fleet-1", "web"))
+        }
+        async fn list_instances(&self, fleet_id: &str, group_id: Option<&str>) -> ComputeResult<Vec<Instance>> {
+            self.called("list_instances")?;
+            Ok(vec![instance(fleet_id, group_id.unwrap_or("all"))])
+        }
+
+        async fn create_function(&self, config: FunctionConfig) -> ComputeResult<Function> {
+            self.called("create_function")?;
+            self.functions.lock().unwrap().insert(config.name.clone(), config.clone());
+            let mut function = Function::new(config.name, config.runtime, config.handler);
+            function.description = config.description;
+            function.vpc_config = config.vpc_config;
+            function.state = FunctionState::Active;
+            Ok(function)
+        }
+        async fn update_function(&self, config: FunctionConfig) -> ComputeResult<Function> { self.create_function(config).await }
+        async fn delete_function(&self, _: &str) -> ComputeResult<()> { self.called("delete_function") }
+        async fn get_function(&self, _: &str) -> ComputeResult<Function> { unsupported() }
+        async fn list_functions(&self) -> ComputeResult<Vec<Function>> { Ok(Vec::new()) }
+        async fn invoke_function(&self, _: &str, payload: Vec<u8>) -> ComputeResult<Vec<u8>> {
+            self.called("invoke_function")?;
+            Ok(payload.into_iter().rev().collect())
+        }
+
+        async fn configure_auto_scaling(&self, config: AutoScalingConfig) -> ComputeResult<AutoScalingConfig> {
+            self.called("configure_auto_scaling")?;
+            self.scaling.lock().unwrap().insert(config.id.clone(), config.clone());
+            Ok(config)
+        }
+        async fn update_auto_scaling(&self, config: AutoScalingConfig) -> ComputeResult<AutoScalingConfig> {
+            self.configure_auto_scaling(config).await
+        }
+        async fn delete_auto_scaling(&self, _: &str) -> ComputeResult<()> { self.called("delete_auto_scaling") }
+        async fn get_auto_scaling(&self, config_id: &str) -> ComputeResult<AutoScalingConfig> {
+            let config = self.scaling.lock().unwrap().get(config_id).cloned();
+            config.ok_or_else(|| ComputeError::NotFound(config_id.to_string()))
+        }
+        async fn list_auto_scaling(&self) -> ComputeResult<Vec<AutoScalingConfig>> {
+            Ok(self.scaling.lock().unwrap().values().cloned().collect())
+        }
+
+        async fn analyze_resources(&self, _: Vec<String>) -> ComputeResult<Vec<OptimizationStrategy>> { unsupported() }
+        async fn apply_optimization(&self, _: OptimizationStrategy) -> ComputeResult<()> { unsupported() }
+        async fn get_optimization_history(&self, _: &str) -> ComputeResult<Vec<OptimizationStrategy>> { unsupported() }
+        async fn get_metrics(&self, _: &str, _: Vec<String>) -> ComputeResult<Vec<(String, f64)>> { unsupported() }
+        async fn get_logs(&self, _: &str, _: i64, _: i64) -> ComputeResult<Vec<String>> { unsupported() }
+    }
+
+    // The generated server is itself a valid transport, so the whole stack runs in-process
+    fn connect(provider: Arc<FakeProvider>) -> ProviderClient<proto::provider_service_server::ProviderServiceServer<ProviderGrpcServer>> {
+        let retry = RetryPolicy::new(3).with_backoff(Duration::from_millis(1), Duration::from_millis(5));
+        ProviderClient::new(ProviderGrpcServer::new(provider).into_service()).with_retry_policy(retry)
+    }
+
+    fn fleet() -> FleetConfig {
+        let mut group = InstanceGroup::new("web".into(), "Web".into(), "m5.large".into()).with_scaling(2, 6, 3);
+        group.startup_script = Some("#!/bin/sh\necho ready".into());
+        group.labels.insert("tier".into(), "frontend".into());
+        group.storage_config.data_volumes.push(VolumeConfig {
+            size: 200,
+            volume_type: VolumeType::Io2,
+            iops: Some(4000),
+            throughput: None,
+            mount_path: "/data".into(),
+            file_system: FileSystem::Xfs,
+        });
+        group.scaling_config = Some(ScalingConfig { metrics: Vec::new(), cooldown_seconds: 120, scale_in_protection: true });
+        let mut fleet = FleetConfig::new("fleet-1".into(), "Production".into());
+        fleet.network_config.vpc_id = Some("vpc-9".into());
+        fleet.network_config.subnet_ids = vec!["subnet-a".into(), "subnet-b".into()];
+        fleet.annotations.insert("owner".into(), "platform".into());
+        fleet.add_instance_group(group);
+        fleet
+    }
+
+    #[tokio::test]
+    async fn test_fleet_operations_round_trip() {
+        let provider = Arc::new(FakeProvider::default());
+        let client = connect(provider.clone());
+
+        let operation = client.create_fleet(fleet()).await.unwrap();
+        let created = client.wait_for_fleet(&operation.id, Duration::from_millis(5), Duration::from_secs(5)).await.unwrap();
+        assert_eq!(json!(created), json!(fleet()));
+        assert_eq!(json!(provider.fleets.lock().unwrap()["fleet-1"]), json!(fleet()));
+        assert!(client.get_operation(&operation.id).await.unwrap().completed_at.is_some());
+
+        // A failed create is reported through the operation, with its error kind intact
+        provider.fail("create_fleet", ComputeError::Conflict("fleet exists".into()));
+        let operation = client.create_fleet(fleet()).await.unwrap();
+        let failed = client.wait_for_fleet(&operation.id, Duration::from_millis(5), Duration::from_secs(5)).await;
+        assert!(matches!(failed, Err(ComputeError::Conflict(msg)) if msg == "fleet exists"));
+
+        assert!(matches!(client.get_fleet("missing").await, Err(ComputeError::NotFound(_))));
+        assert!(matches!(client.get_operation("missing").await, Err(ComputeError::NotFound(_))));
+
+        let instances = client.list_instances("fleet-1", Some("web")).await.unwrap();
+        assert_eq!(json!(instances), json!([instance("fleet-1", "web")]));
+    }
+
+    #[tokio::test]
+    async fn test_functions_and_autoscaling_map_every_field() {
+        let provider = Arc::new(FakeProvider::default());
+        let client = connect(provider.clone());
+
+        let mut function = FunctionConfig {
+            name: "resize".into(),
+            runtime: "nodejs18.x".into(),
+            handler: "index.handler".into(),
+            code: Vec::new(),
+            code_ref: Some(CodeRef { digest: "sha256:ab".into(), location: "s3://pkgs/resize.zip".into(), size_bytes: 9_000_000 }),
+            description: Some("Thumbnails".into()),
+            memory_mb: 512,
+            timeout_sec: 30,
+            environment: HashMap::from([("STAGE".to_string(), "prod".to_string())]),
+            labels: HashMap::new(),
+            annotations: HashMap::new(),
+            vpc_config: Some(VpcConfig { subnet_ids: vec!["subnet-a".into()], security_group_ids: vec!["sg-1".into()] }),
+            provisioned_concurrency: Some(5),
+        };
+        let created = client.create_function(function.clone()).await.unwrap();
+        assert_eq!(created.state, FunctionState::Active);
+        assert_eq!(json!(provider.functions.lock().unwrap()["resize"]), json!(function));
+        function.code = vec![1, 2, 3];
+        client.update_function(function.clone()).await.unwrap();
+        assert_eq!(provider.functions.lock().unwrap()["resize"].code, vec![1, 2, 3]);
+        assert_eq!(client.invoke_function("resize", vec![1, 2, 3]).await.unwrap(), vec![3, 2, 1]);
+
+        let mut scaling = AutoScalingConfig::new("asg-1".into(), "web".into(), "web".into(), ResourceType::InstanceGroup)
+            .with_capacity(2, 10, 4);
+        scaling.add_metric(ScalingMetric {
+            name: "CPUUtilization".into(),
+            namespace: "AWS/EC2".into(),
+            dimensions: HashMap::from([("AutoScalingGroupName".to_string(), "web".to_string())]),
+            statistic: MetricStatistic::Percentile(99.5),
+            comparison: ComparisonOperator::GreaterThanOrEqualToThreshold,
+            threshold: 70.0,
+            period_seconds: 60,
+            evaluation_periods: 3,
+            datapoints_to_alarm: 2,
+            scale_adjustment: -1,
+        });
+        scaling.add_schedule(ScalingSchedule {
+            name: "nightly".into(),
+            recurrence: "0 22 * * *".into(),
+            min_capacity: Some(1),
+            max_capacity: None,
+            desired_capacity: Some(1),
+            start_time: Some(Utc.with_ymd_and_hms(2026, 10, 1, 22, 0, 0).unwrap()),
+            end_time: None,
+        });
+        let configured = client.configure_auto_scaling(scaling.clone()).await.unwrap();
+        assert_eq!(json!(configured), json!(scaling));
+        assert_eq!(json!(client.get_auto_scaling("asg-1").await.unwrap()), json!(scaling));
+    }
+
+    #[tokio::test]
+    async fn test_retries_follow_the_error_taxonomy() {
+        let provider = Arc::new(FakeProvider::default());
+        let client = connect(provider.clone());
+        provider.fleets.lock().unwrap().insert("fleet-1".into(), fleet());
+
+        provider.fail("get_fleet", ComputeError::Throttled("slow down".into()));
+        provider.fail("get_fleet", ComputeError::Unavailable("blip".into()));
+        assert!(client.get_fleet("fleet-1").await.is_ok());
+        assert_eq!(provider.calls("get_fleet"), 3);
+
+        provider.fail("get_fleet", ComputeError::Validation("bad id".into()));
+        assert!(matches!(client.get_fleet("fleet-1").await, Err(ComputeError::Validation(_))));
+        assert_eq!(provider.calls("get_fleet"), 4);
+
+        // A failed invocation may already have run, so only throttling is retried
+        provider.fail("invoke_function", ComputeError::Unavailable("timed out".into()));
+        assert!(matches!(client.invoke_function("resize", vec![1]).await, Err(ComputeError::Unavailable(_))));
+        assert_eq!(provider.calls("invoke_function"), 1);
+        provider.fail("invoke_function", ComputeError::Throttled("slow down".into()));
+        assert!(client.invoke_function("resize", vec![1]).await.is_ok());
+        assert_eq!(provider.calls("invoke_function"), 3);
+    }
+}
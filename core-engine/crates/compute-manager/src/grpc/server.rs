@@ -0,0 +1,241 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use chrono::{Duration, Utc};
+use opentelemetry::trace::FutureExt;
+use opentelemetry::Context;
+use tonic::{Request, Response, Status};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use super::convert::to_millis;
+use super::extract_trace_context;
+use super::proto::{self, provider_service_server::ProviderService, provider_service_server::ProviderServiceServer};
+use crate::error::{ComputeError, ComputeResult};
+use crate::provider::Provider;
+
+pub const CREATE_FLEET_OPERATION: &str = "create_fleet";
+
+// Finished operations stay pollable this long
+const OPERATION_RETENTION_HOURS: i64 = 24;
+
+type RpcResult<T> = Result<Response<T>, Status>;
+
+// Serves `ProviderService` by dispatching every call to the configured provider
+pub struct ProviderGrpcServer {
+    provider: Arc<dyn Provider>,
+    operations: Arc<Mutex<HashMap<String, proto::Operation>>>,
+}
+
+impl ProviderGrpcServer {
+    pub fn new(provider: Arc<dyn Provider>) -> Self {
+        Self { provider, operations: Arc::new(Mutex::new(HashMap::new())) }
+    }
+
+    pub fn into_service(self) -> ProviderServiceServer<Self> {
+        ProviderServiceServer::new(self)
+    }
+
+    fn start_operation(&self, kind: &str) -> proto::Operation {
+        let now = Utc::now();
+        let operation = proto::Operation {
+            id: Uuid::new_v4().to_string(),
+            kind: kind.to_string(),
+            state: proto::OperationState::Running as i32,
+            created_at: to_millis(now),
+            completed_at: None,
+            result: None,
+        };
+        let cutoff = to_millis(now - Duration::hours(OPERATION_RETENTION_HOURS));
+        let mut operations = self.operations.lock().unwrap();
+        operations.retain(|_, op| op.completed_at.is_none_or(|done| done > cutoff));
+        operations.insert(operation.id.clone(), operation.clone());
+        operation
+    }
+}
+
+fn finish(operations: &Mutex<HashMap<String, proto::Operation>>, id: &str, result: Result<proto::FleetConfig, ComputeError>) {
+    let mut operations = operations.lock().unwrap();
+    let Some(operation) = operations.get_mut(id) else { return };
+    operation.completed_at = Some(to_millis(Utc::now()));
+    match result {
+        Ok(fleet) => {
+            operation.state = proto::OperationState::Succeeded as i32;
+            operation.result = Some(proto::operation::Result::Fleet(fleet));
+        }
+        Err(e) => {
+            let status = Status::from(e);
+            operation.state = proto::OperationState::Failed as i32;
+            operation.result = Some(proto::operation::Result::Error(proto::OperationError {
+                code: status.code() as i32,
+                message: status.message().to_string(),
+            }));
+        }
+    }
+}
+
+fn unpack<T>(request: Request<T>) -> (Context, T) {
+    (extract_trace_context(request.metadata()), request.into_inner())
+}
+
+fn decode<T, P: TryInto<T, Error = ComputeError>>(message: Option<P>, field: &str) -> ComputeResult<T> {
+    message.ok_or_else(|| ComputeError::Validation(format!("{} is required", field)))?.try_into()
+}
+
+#[tonic::async_trait]
+impl ProviderService for ProviderGrpcServer {
+    async fn create_fleet(&self, request: Request<proto::CreateFleetRequest>) -> RpcResult<proto::Operation> {
+        let (cx, req) = unpack(request);
+        let fleet = decode(req.fleet, "fleet")?;
+        let operation = self.start_operation(CREATE_FLEET_OPERATION);
+
+        // Creating a fleet can take minutes, so the caller polls GetOperation instead of waiting
+        let (provider, operations, id) = (self.provider.clone(), self.operations.clone(), operation.id.clone());
+        tokio::spawn(
+            async move {
+                let result = provider.create_fleet(fleet).await;
+                match &result {
+                    Ok(fleet) => info!("Operation {} created fleet {}", id, fleet.id),
+                    Err(e) => warn!("Operation {} failed to create fleet: {}", id, e),
+                }
+                finish(&operations, &id, result.map(Into::into));
+            }
+            .with_context(cx),
+        );
+        Ok(Response::new(operation))
+    }
+
+    async fn update_fleet(&self, request: Request<proto::UpdateFleetRequest>) -> RpcResult<proto::FleetConfig> {
+        let (cx, req) = unpack(request);
+        let fleet = self.provider.update_fleet(decode(req.fleet, "fleet")?).with_context(cx).await?;
+        Ok(Response::new(fleet.into()))
+    }
+
+    async fn delete_fleet(&self, request: Request<proto::DeleteFleetRequest>) -> RpcResult<proto::Empty> {
+        let (cx, req) = unpack(request);
+        self.provider.delete_fleet(&req.fleet_id).with_context(cx).await?;
+        Ok(Response::new(proto::Empty {}))
+    }
+
+    async fn get_fleet(&self, request: Request<proto::GetFleetRequest>) -> RpcResult<proto::FleetConfig> {
+        let (cx, req) = unpack(request);
+        let fleet = self.provider.get_fleet(&req.fleet_id).with_context(cx).await?;
+        Ok(Response::new(fleet.into()))
+    }
+
+    async fn list_fleets(&self, request: Request<proto::ListFleetsRequest>) -> RpcResult<proto::ListFleetsResponse> {
+        let (cx, _) = unpack(request);
+        let fleets = self.provider.list_fleets().with_context(cx).await?;
+        Ok(Response::new(proto::ListFleetsResponse { fleets: fleets.into_iter().map(Into::into).collect() }))
+    }
+
+    async fn get_operation(&self, request: Request<proto::GetOperationRequest>) -> RpcResult<proto::Operation> {
+        let req = request.into_inner();
+        let operation = self.operations.lock().unwrap().get(&req.operation_id).cloned();
+        operation
+            .map(Response::new)
+            .ok_or_else(|| Status::not_found(format!("operation {} not found", req.operation_id)))
+    }
+
+    async fn start_instance(&self, request: Request<proto::InstanceRequest>) -> RpcResult<proto::Empty> {
+        let (cx, req) = unpack(request);
+        self.provider.start_instance(&req.instance_id).with_context(cx).await?;
+        Ok(Response::new(proto::Empty {}))
+    }
+
+    async fn stop_instance(&self, request: Request<proto::InstanceRequest>) -> RpcResult<proto::Empty> {
+        let (cx, req) = unpack(request);
+        self.provider.stop_instance(&req.instance_id).with_context(cx).await?;
+        Ok(Response::new(proto::Empty {}))
+    }
+
+    async fn restart_instance(&self, request: Request<proto::InstanceRequest>) -> RpcResult<proto::Empty> {
+        let (cx, req) = unpack(request);
+        self.provider.restart_instance(&req.instance_id).with_context(cx).await?;
+        Ok(Response::new(proto::Empty {}))
+    }
+
+    async fn terminate_instance(&self, request: Request<proto::InstanceRequest>) -> RpcResult<proto::Empty> {
+        let (cx, req) = unpack(request);
+        self.provider.terminate_instance(&req.instance_id).with_context(cx).await?;
+        Ok(Response::new(proto::Empty {}))
+    }
+
+    async fn get_instance(&self, request: Request<proto::InstanceRequest>) -> RpcResult<proto::Instance> {
+        let (cx, req) = unpack(request);
+        let instance = self.provider.get_instance(&req.instance_id).with_context(cx).await?;
+        Ok(Response::new(instance.into()))
+    }
+
+    async fn list_instances(&self, request: Request<proto::ListInstancesRequest>) -> RpcResult<proto::ListInstancesResponse> {
+        let (cx, req) = unpack(request);
+        let instances = self.provider.list_instances(&req.fleet_id, req.group_id.as_deref()).with_context(cx).await?;
+        Ok(Response::new(proto::ListInstancesResponse { instances: instances.into_iter().map(Into::into).collect() }))
+    }
+
+    async fn create_function(&self, request: Request<proto::FunctionConfig>) -> RpcResult<proto::Function> {
+        let (cx, req) = unpack(request);
+        let function = self.provider.create_function(req.into()).with_context(cx).await?;
+        Ok(Response::new(function.into()))
+    }
+
+    async fn update_function(&self, request: Request<proto::FunctionConfig>) -> RpcResult<proto::Function> {
+        let (cx, req) = unpack(request);
+        let function = self.provider.update_function(req.into()).with_context(cx).await?;
+        Ok(Response::new(function.into()))
+    }
+
+    async fn delete_function(&self, request: Request<proto::FunctionRequest>) -> RpcResult<proto::Empty> {
+        let (cx, req) = unpack(request);
+        self.provider.delete_function(&req.function_id).with_context(cx).await?;
+        Ok(Response::new(proto::Empty {}))
+    }
+
+    async fn get_function(&self, request: Request<proto::FunctionRequest>) -> RpcResult<proto::Function> {
+        let (cx, req) = unpack(request);
+        let function = self.provider.get_function(&req.function_id).with_context(cx).await?;
+        Ok(Response::new(function.into()))
+    }
+
+    async fn list_functions(&self, request: Request<proto::ListFunctionsRequest>) -> RpcResult<proto::ListFunctionsResponse> {
+        let (cx, _) = unpack(request);
+        let functions = self.provider.list_functions().with_context(cx).await?;
+        Ok(Response::new(proto::ListFunctionsResponse { functions: functions.into_iter().map(Into::into).collect() }))
+    }
+
+    async fn invoke_function(&self, request: Request<proto::InvokeFunctionRequest>) -> RpcResult<proto::InvokeFunctionResponse> {
+        let (cx, req) = unpack(request);
+        let payload = self.provider.invoke_function(&req.function_id, req.payload).with_context(cx).await?;
+        Ok(Response::new(proto::InvokeFunctionResponse { payload }))
+    }
+
+    async fn configure_auto_scaling(&self, request: Request<proto::AutoScalingConfig>) -> RpcResult<proto::AutoScalingConfig> {
+        let (cx, req) = unpack(request);
+        let config = self.provider.configure_auto_scaling(req.try_into()?).with_context(cx).await?;
+        Ok(Response::new(config.into()))
+    }
+
+    async fn update_auto_scaling(&self, request: Request<proto::AutoScalingConfig>) -> RpcResult<proto::AutoScalingConfig> {
+        let (cx, req) = unpack(request);
+        let config = self.provider.update_auto_scaling(req.try_into()?).with_context(cx).await?;
+        Ok(Response::new(config.into()))
+    }
+
+    async fn delete_auto_scaling(&self, request: Request<proto::AutoScalingRequest>) -> RpcResult<proto::Empty> {
+        let (cx, req) = unpack(request);
+        self.provider.delete_auto_scaling(&req.config_id).with_context(cx).await?;
+        Ok(Response::new(proto::Empty {}))
+    }
+
+    async fn get_auto_scaling(&self, request: Request<proto::AutoScalingRequest>) -> RpcResult<proto::AutoScalingConfig> {
+        let (cx, req) = unpack(request);
+        let config = self.provider.get_auto_scaling(&req.config_id).with_context(cx).await?;
+        Ok(Response::new(config.into()))
+    }
+
+    async fn list_auto_scaling(&self, request: Request<proto::ListAutoScalingRequest>) -> RpcResult<proto::ListAutoScalingResponse> {
+        let (cx, _) = unpack(request);
+        let configs = self.provider.list_auto_scaling().with_context(cx).await?;
+        Ok(Response::new(proto::ListAutoScalingResponse { configs: configs.into_iter().map(Into::into).collect() }))
+    }
+}
@@ -1,11 +1,25 @@
 // Core compute manager functionality
+pub mod error;
+pub mod credentials;
+pub mod fleet;
+pub mod serverless;
 pub mod cloud;
 pub mod scaling;
 pub mod metrics;
 pub mod config;
+pub mod autoscaling;
+pub mod optimization;
+pub mod provider;
+pub mod grpc;
+pub mod governor;
+pub mod cache;
 
 // Re-export commonly used items
+pub use error::{ComputeError, ComputeResult};
 pub use cloud::*;
 pub use scaling::*;
 pub use metrics::*;
 pub use config::*;
+pub use grpc::{ProviderClient, ProviderGrpcServer};
+pub use governor::{GovernedProvider, GovernedRequest, RateGovernor};
+pub use cache::{CachedProvider, InventoryCache};
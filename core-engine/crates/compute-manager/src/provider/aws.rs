@@ -1,25 +1,43 @@
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
 use async_trait::async_trait;
 use aws_sdk_ec2::{Client as Ec2Client, Config as Ec2Config};
 use aws_sdk_lambda::{Client as LambdaClient, Config as LambdaConfig};
 use aws_sdk_autoscaling::{Client as AutoScalingClient, Config as AutoScalingConfig as AwsAutoScalingConfig};
 use aws_sdk_cloudwatch::{Client as CloudWatchClient, Config as CloudWatchConfig};
+use aws_sdk_ssm::Client as SsmClient;
+use aws_sdk_secretsmanager::Client as SecretsManagerClient;
+use aws_sdk_ssm::types::CommandInvocationStatus;
 use aws_config::SdkConfig;
 use aws_types::region::Region;
 use aws_types::credentials::{ProvideCredentials, Credentials as AwsCredentials};
+use aws_sdk_ec2::error::ProvideErrorMetadata;
 
 use crate::error::{ComputeError, ComputeResult};
 use crate::fleet::{FleetConfig, Instance, InstanceGroup};
-use crate::serverless::{Function, FunctionConfig};
+use crate::fleet::command::{CommandBackend, CommandOutput, CommandPlatform, CommandPoll, RemoteCommand};
+use crate::fleet::import::{ImportSource, LiveGroup, LiveScalingPolicy, LiveVolume};
+use crate::serverless::concurrency::{self, InvocationLog, NativeConcurrency};
+use crate::serverless::secrets::{self, NativeSecretStore, SecretEnvironment};
+use crate::serverless::{Function, FunctionConfig, FunctionInvocation, FunctionState};
 use crate::autoscaling::AutoScalingConfig;
 use crate::optimization::OptimizationStrategy;
 use super::{Provider, ProviderConfig, ProviderType};
+use base64::Engine;
+use sirsi_key_vault::SecretString;
+
+// Deploys publish a version and point this alias at it; provisioned concurrency lives on the
+// alias and invocations go through it
+const LAMBDA_ALIAS: &str = "live";
 
 pub struct AwsProvider {
     ec2_client: Ec2Client,
     lambda_client: LambdaClient,
     autoscaling_client: AutoScalingClient,
     cloudwatch_client: CloudWatchClient,
+    ssm_client: SsmClient,
+    secrets_client: SecretsManagerClient,
+    invocations: InvocationLog,
     config: ProviderConfig,
 }
 
@@ -31,11 +49,12 @@ impl Provider for AwsProvider {
         }
 
         let region = Region::new(config.region.clone());
-        let aws_config = if config.credentials.access_key.is_some() && config.credentials.secret_key.is_some() {
+        let resolved = config.resolve_credentials().await?;
+        let aws_config = if let (Some(access_key), Some(secret_key)) = (&resolved.access_key, &resolved.secret_key) {
             let credentials = AwsCredentials::from_keys(
-                config.credentials.access_key.as_ref().unwrap(),
-                config.credentials.secret_key.as_ref().unwrap(),
-                config.credentials.token.clone(),
+                access_key,
+                secret_key.expose(),
+                resolved.token.as_ref().map(|token| token.expose().to_string()),
             );
             aws_config::from_env()
                 .region(region)
@@ -53,12 +72,17 @@ impl Provider for AwsProvider {
         let lambda_client = LambdaClient::new(&aws_config);
         let autoscaling_client = AutoScalingClient::new(&aws_config);
         let cloudwatch_client = CloudWatchClient::new(&aws_config);
+        let ssm_client = SsmClient::new(&aws_config);
+        let secrets_client = SecretsManagerClient::new(&aws_config);
 
         Ok(Box::new(Self {
             ec2_client,
             lambda_client,
             autoscaling_client,
             cloudwatch_client,
+            ssm_client,
+            secrets_client,
+            invocations: InvocationLog::default(),
             config,
         }))
     }
@@ -174,7 +198,7 @@ impl Provider for AwsProvider {
             .instance_ids(instance_id)
             .send()
             .await
-            .map_err(|e| ComputeError::Provider(format!("Failed to start instance: {}", e)))?;
+            .map_err(|e| aws_error("Failed to start instance", e))?;
         Ok(())
     }
 
@@ -184,7 +208,7 @@ impl Provider for AwsProvider {
             .instance_ids(instance_id)
             .send()
             .await
-            .map_err(|e| ComputeError::Provider(format!("Failed to stop instance: {}", e)))?;
+            .map_err(|e| aws_error("Failed to stop instance", e))?;
         Ok(())
     }
 
@@ -194,7 +218,7 @@ impl Provider for AwsProvider {
             .instance_ids(instance_id)
             .send()
             .await
-            .map_err(|e| ComputeError::Provider(format!("Failed to restart instance: {}", e)))?;
+            .map_err(|e| aws_error("Failed to restart instance", e))?;
         Ok(())
     }
 
@@ -204,7 +228,7 @@ impl Provider for AwsProvider {
             .instance_ids(instance_id)
             .send()
             .await
-            .map_err(|e| ComputeError::Provider(format!("Failed to terminate instance: {}", e)))?;
+            .map_err(|e| aws_error("Failed to terminate instance", e))?;
         Ok(())
     }
 
@@ -214,7 +238,7 @@ impl Provider for AwsProvider {
             .instance_ids(instance_id)
             .send()
             .await
-            .map_err(|e| ComputeError::Provider(format!("Failed to get instance: {}", e)))?;
+            .map_err(|e| aws_error("Failed to get instance", e))?;
 
         let instance = resp
             .reservations()
@@ -233,7 +257,7 @@ impl Provider for AwsProvider {
             .filters("tag:FleetId", fleet_id)
             .send()
             .await
-            .map_err(|e| ComputeError::Provider(format!("Failed to list instances: {}", e)))?;
+            .map_err(|e| aws_error("Failed to list instances", e))?;
 
         if let Some(reservations) = resp.reservations() {
             for reservation in reservations {
@@ -258,17 +282,27 @@ impl Provider for AwsProvider {
 
     // Serverless Functions
     async fn create_function(&self, config: FunctionConfig) -> ComputeResult<Function> {
+        // Packages already in S3 are handed to Lambda by reference instead of re-uploaded
+        let code = match config.code_ref.as_ref().and_then(|code_ref| code_ref.s3_location()) {
+            Some((bucket, key)) => aws_sdk_lambda::types::FunctionCode::builder().s3_bucket(bucket).s3_key(key).build(),
+            None => aws_sdk_lambda::types::FunctionCode::builder()
+                .zip_file(config.resolve_code().await?.into())
+                .build(),
+        };
         let resp = self.lambda_client
             .create_function()
             .function_name(&config.name)
             .runtime(&config.runtime)
             .handler(&config.handler)
-            .code(config.code)
+            .code(code)
             .memory_size(config.memory_mb as i32)
             .timeout(config.timeout_sec as i32)
+            .environment(self.function_environment(&config.environment).await?)
+            .publish(true)
             .send()
             .await
-            .map_err(|e| ComputeError::Provider(format!("Failed to create function: {}", e)))?;
+            .map_err(|e| aws_error("Failed to create function", e))?;
+        self.point_alias(&config, resp.version().unwrap_or("$LATEST")).await?;
 
         self.convert_to_function(resp)
     }
@@ -279,9 +313,17 @@ impl Provider for AwsProvider {
             .function_name(&config.name)
             .memory_size(config.memory_mb as i32)
             .timeout(config.timeout_sec as i32)
+            .environment(self.function_environment(&config.environment).await?)
+            .send()
+            .await
+            .map_err(|e| aws_error("Failed to update function", e))?;
+        let version = self.lambda_client
+            .publish_version()
+            .function_name(&config.name)
             .send()
             .await
-            .map_err(|e| ComputeError::Provider(format!("Failed to update function: {}", e)))?;
+            .map_err(|e| aws_error("Failed to publish function version", e))?;
+        self.point_alias(&config, version.version().unwrap_or("$LATEST")).await?;
 
         self.convert_to_function(resp)
     }
@@ -292,7 +334,7 @@ impl Provider for AwsProvider {
             .function_name(function_id)
             .send()
             .await
-            .map_err(|e| ComputeError::Provider(format!("Failed to delete function: {}", e)))?;
+            .map_err(|e| aws_error("Failed to delete function", e))?;
         Ok(())
     }
 
@@ -302,7 +344,7 @@ impl Provider for AwsProvider {
             .function_name(function_id)
             .send()
             .await
-            .map_err(|e| ComputeError::Provider(format!("Failed to get function: {}", e)))?;
+            .map_err(|e| aws_error("Failed to get function", e))?;
 
         self.convert_to_function(resp.configuration().unwrap())
     }
@@ -320,7 +362,7 @@ impl Provider for AwsProvider {
             let resp = req
                 .send()
                 .await
-                .map_err(|e| ComputeError::Provider(format!("Failed to list functions: {}", e)))?;
+                .map_err(|e| aws_error("Failed to list functions", e))?;
 
             if let Some(fns) = resp.functions() {
                 for f in fns {
@@ -341,14 +383,42 @@ impl Provider for AwsProvider {
     }
 
     async fn invoke_function(&self, function_id: &str, payload: Vec<u8>) -> ComputeResult<Vec<u8>> {
-        let resp = self.lambda_client
-            .invoke()
-            .function_name(function_id)
-            .payload(payload.into())
-            .send()
-            .await
-            .map_err(|e| ComputeError::Provider(format!("Failed to invoke function: {}", e)))?;
-
+        let start_time = chrono::Utc::now();
+        let invoke = |qualifier: Option<&str>| {
+            self.lambda_client
+                .invoke()
+                .function_name(function_id)
+                .set_qualifier(qualifier.map(str::to_string))
+                .log_type(aws_sdk_lambda::types::LogType::Tail)
+                .payload(payload.clone().into())
+                .send()
+        };
+        let resp = match invoke(Some(LAMBDA_ALIAS)).await {
+            // Deployed before deploys published an alias
+            Err(e) if e.code() == Some("ResourceNotFoundException") => invoke(None).await,
+            resp => resp,
+        }
+        .map_err(|e| aws_error("Failed to invoke function", e))?;
+
+        let log_tail = resp
+            .log_result()
+            .and_then(|tail| base64::engine::general_purpose::STANDARD.decode(tail).ok())
+            .map(|tail| String::from_utf8_lossy(&tail).into_owned())
+            .unwrap_or_default();
+        let report = concurrency::parse_lambda_report(&log_tail);
+        let end_time = chrono::Utc::now();
+        self.invocations.record(FunctionInvocation {
+            function_name: function_id.to_string(),
+            request_id: uuid::Uuid::new_v4().to_string(),
+            start_time,
+            end_time: Some(end_time),
+            duration_ms: report.duration_ms.or(Some((end_time - start_time).num_milliseconds())),
+            memory_used_mb: None,
+            error: resp.function_error().map(str::to_string),
+            logs: Vec::new(),
+            cold_start: report.cold_start,
+            init_duration_ms: report.init_duration_ms,
+        });
         Ok(resp.payload().unwrap().as_ref().to_vec())
     }
 
@@ -451,7 +521,7 @@ impl Provider for AwsProvider {
                 .statistics("Average")
                 .send()
                 .await
-                .map_err(|e| ComputeError::Provider(format!("Failed to get metrics: {}", e)))?;
+                .map_err(|e| aws_error("Failed to get metrics", e))?;
 
             if let Some(datapoints) = resp.datapoints() {
                 if let Some(datapoint) = datapoints.first() {
@@ -478,7 +548,7 @@ impl Provider for AwsProvider {
             .end_time(end.timestamp_millis())
             .send()
             .await
-            .map_err(|e| ComputeError::Provider(format!("Failed to get logs: {}", e)))?;
+            .map_err(|e| aws_error("Failed to get logs", e))?;
 
         if let Some(events) = resp.events() {
             for event in events {
@@ -492,6 +562,318 @@ impl Provider for AwsProvider {
     }
 }
 
+#[async_trait]
+impl ImportSource for AwsProvider {
+    async fn describe_group(&self, identifier: &str) -> ComputeResult<LiveGroup> {
+        let name = asg_name(identifier);
+        let resp = self.autoscaling_client
+            .describe_auto_scaling_groups()
+            .auto_scaling_group_names(name)
+            .send()
+            .await
+            .map_err(|e| aws_error("Failed to describe auto scaling group", e))?;
+
+        let asg = resp.auto_scaling_groups()
+            .and_then(|groups| groups.first())
+            .ok_or_else(|| ComputeError::NotFound(format!("Auto scaling group {} not found", identifier)))?;
+
+        let mut live = LiveGroup {
+            identifier: identifier.to_string(),
+            name: asg.auto_scaling_group_name().unwrap_or(name).to_string(),
+            instance_type: String::new(),
+            min_size: asg.min_size().unwrap_or(0),
+            max_size: asg.max_size().unwrap_or(0),
+            desired_size: asg.desired_capacity().unwrap_or(0),
+            tags: HashMap::new(),
+            vpc_id: None,
+            subnet_ids: asg.vpc_zone_identifier()
+                .map(|ids| ids.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect())
+                .unwrap_or_default(),
+            security_groups: Vec::new(),
+            associate_public_ip: false,
+            root_volume_size: None,
+            data_volumes: Vec::new(),
+            user_data: None,
+            scaling_policies: Vec::new(),
+            cooldown_seconds: asg.default_cooldown(),
+            scale_in_protection: asg.new_instances_protected_from_scale_in().unwrap_or(false),
+            update_in_progress: false,
+            attributes: BTreeMap::new(),
+        };
+
+        for tag in asg.tags().unwrap_or_default() {
+            if let (Some(key), Some(value)) = (tag.key(), tag.value()) {
+                live.tags.insert(key.to_string(), value.to_string());
+            }
+        }
+
+        if asg.capacity_rebalance().unwrap_or(false) {
+            live.attributes.insert("capacity_rebalance".into(), serde_json::Value::Bool(true));
+        }
+        if let Some(policy) = asg.mixed_instances_policy() {
+            live.attributes.insert("mixed_instances_policy".into(), serde_json::Value::String(format!("{:?}", policy)));
+        }
+        if let Some(warm_pool) = asg.warm_pool_configuration() {
+            live.attributes.insert("warm_pool".into(), serde_json::Value::String(format!("{:?}", warm_pool)));
+        }
+        if let Some(target_groups) = asg.target_group_ar_ns() {
+            if !target_groups.is_empty() {
+                live.attributes.insert("target_group_arns".into(), serde_json::json!(target_groups));
+            }
+        }
+
+        if let Some(template) = asg.launch_template() {
+            let resp = self.ec2_client
+                .describe_launch_template_versions()
+                .set_launch_template_id(template.launch_template_id().map(String::from))
+                .set_launch_template_name(template.launch_template_name().map(String::from))
+                .versions(template.version().unwrap_or("$Default"))
+                .send()
+                .await
+                .map_err(|e| aws_error("Failed to describe launch template", e))?;
+
+            if let Some(data) = resp.launch_template_versions()
+                .and_then(|versions| versions.first())
+                .and_then(|version| version.launch_template_data())
+            {
+                live.instance_type = data.instance_type().map(|t| t.as_str().to_string()).unwrap_or_default();
+                live.user_data = data.user_data().map(String::from);
+                live.security_groups = data.security_group_ids().unwrap_or_default().to_vec();
+
+                for interface in data.network_interfaces().unwrap_or_default() {
+                    live.associate_public_ip |= interface.associate_public_ip_address().unwrap_or(false);
+                    live.security_groups.extend(interface.groups().unwrap_or_default().iter().cloned());
+                }
+
+                for mapping in data.block_device_mappings().unwrap_or_default() {
+                    let (Some(device), Some(ebs)) = (mapping.device_name(), mapping.ebs()) else {
+                        continue;
+                    };
+                    if device == "/dev/xvda" || device == "/dev/sda1" {
+                        live.root_volume_size = ebs.volume_size();
+                        continue;
+                    }
+                    live.data_volumes.push(LiveVolume {
+                        device_name: device.to_string(),
+                        size: ebs.volume_size().unwrap_or(0),
+                        volume_type: ebs.volume_type().map(|t| t.as_str().to_string()).unwrap_or_default(),
+                        iops: ebs.iops(),
+                        throughput: ebs.throughput(),
+                        encrypted: ebs.encrypted().unwrap_or(false),
+                    });
+                }
+
+                if data.instance_market_options().is_some() {
+                    live.attributes.insert("instance_market_options".into(), serde_json::Value::String("spot".into()));
+                }
+            }
+        }
+
+        if let Some(subnet) = live.subnet_ids.first() {
+            let resp = self.ec2_client
+                .describe_subnets()
+                .subnet_ids(subnet)
+                .send()
+                .await
+                .map_err(|e| aws_error("Failed to describe subnet", e))?;
+            live.vpc_id = resp.subnets()
+                .and_then(|subnets| subnets.first())
+                .and_then(|subnet| subnet.vpc_id())
+                .map(String::from);
+        }
+
+        let resp = self.autoscaling_client
+            .describe_instance_refreshes()
+            .auto_scaling_group_name(&live.name)
+            .max_records(1)
+            .send()
+            .await
+            .map_err(|e| aws_error("Failed to describe instance refreshes", e))?;
+        live.update_in_progress = resp.instance_refreshes()
+            .and_then(|refreshes| refreshes.first())
+            .and_then(|refresh| refresh.status())
+            .map(|status| matches!(status.as_str(), "Pending" | "InProgress" | "Cancelling" | "RollbackInProgress"))
+            .unwrap_or(false);
+
+        let resp = self.autoscaling_client
+            .describe_policies()
+            .auto_scaling_group_name(&live.name)
+            .send()
+            .await
+            .map_err(|e| aws_error("Failed to describe scaling policies", e))?;
+
+        for policy in resp.scaling_policies().unwrap_or_default() {
+            if let Some(target) = policy.target_tracking_configuration() {
+                let metric = target.predefined_metric_specification()
+                    .map(|m| m.predefined_metric_type().as_str().to_string())
+                    .unwrap_or_else(|| "custom".to_string());
+                live.scaling_policies.push(LiveScalingPolicy {
+                    name: policy.policy_name().unwrap_or_default().to_string(),
+                    metric,
+                    target_value: target.target_value().unwrap_or_default(),
+                });
+            } else if let Some(name) = policy.policy_name() {
+                live.attributes.insert(format!("scaling_policy.{}", name), serde_json::Value::String(
+                    policy.policy_type().unwrap_or_default().to_string(),
+                ));
+            }
+        }
+
+        Ok(live)
+    }
+
+    async fn tag_group(&self, identifier: &str, tags: HashMap<String, String>) -> ComputeResult<()> {
+        let name = asg_name(identifier);
+        let tags = tags.into_iter()
+            .map(|(key, value)| {
+                aws_sdk_autoscaling::types::Tag::builder()
+                    .resource_id(name)
+                    .resource_type("auto-scaling-group")
+                    .key(key)
+                    .value(value)
+                    .propagate_at_launch(true)
+                    .build()
+            })
+            .collect::<Vec<_>>();
+
+        self.autoscaling_client
+            .create_or_update_tags()
+            .set_tags(Some(tags))
+            .send()
+            .await
+            .map_err(|e| aws_error("Failed to tag auto scaling group", e))?;
+
+        Ok(())
+    }
+
+    async fn update_group(&self, identifier: &str, group: &InstanceGroup) -> ComputeResult<()> {
+        // Only capacity is pushed; the launch template and unmodeled settings stay as they are
+        let name = asg_name(identifier);
+        self.autoscaling_client
+            .update_auto_scaling_group()
+            .auto_scaling_group_name(name)
+            .min_size(group.min_size)
+            .max_size(group.max_size)
+            .desired_capacity(group.desired_size)
+            .send()
+            .await
+            .map_err(|e| aws_error("Failed to update auto scaling group", e))?;
+
+        Ok(())
+    }
+}
+
+// Accepts either a bare ASG name or its full ARN
+fn asg_name(identifier: &str) -> &str {
+    identifier
+        .rsplit(':')
+        .next()
+        .unwrap_or(identifier)
+        .trim_start_matches("autoScalingGroupName/")
+}
+
+// AWS reports throttling and other failure classes via error codes rather than types
+// Remote commands go through SSM RunCommand, so instances need the SSM agent and an instance
+// profile allowing it
+#[async_trait]
+impl CommandBackend for AwsProvider {
+    async fn list_instances(&self, fleet_id: &str, group_id: &str) -> ComputeResult<Vec<Instance>> {
+        Provider::list_instances(self, fleet_id, Some(group_id)).await
+    }
+
+    async fn start(&self, instance: &Instance, command: &RemoteCommand) -> ComputeResult<String> {
+        let document = match command.platform {
+            CommandPlatform::Linux => "AWS-RunShellScript",
+            CommandPlatform::Windows => "AWS-RunPowerShellScript",
+        };
+        let mut request = self.ssm_client
+            .send_command()
+            .document_name(document)
+            .instance_ids(&instance.instance_id)
+            .parameters("commands", vec![command.script.clone()])
+            .parameters("executionTimeout", vec![command.timeout_seconds.to_string()])
+            .comment("SirsiNexus remote command");
+        if let Some(dir) = &command.working_directory {
+            request = request.parameters("workingDirectory", vec![dir.clone()]);
+        }
+        let resp = request
+            .send()
+            .await
+            .map_err(|e| aws_error("Failed to send command", e))?;
+
+        resp.command()
+            .and_then(|c| c.command_id())
+            .map(str::to_string)
+            .ok_or_else(|| ComputeError::Provider("SSM returned no command id".into()))
+    }
+
+    async fn poll(&self, instance: &Instance, handle: &str) -> ComputeResult<CommandPoll> {
+        let resp = match self.ssm_client
+            .get_command_invocation()
+            .command_id(handle)
+            .instance_id(&instance.instance_id)
+            .send()
+            .await
+        {
+            Ok(resp) => resp,
+            // The invocation isn't visible for a moment after SendCommand returns
+            Err(e) if e.code() == Some("InvocationDoesNotExist") => return Ok(CommandPoll::Running),
+            Err(e) => return Err(aws_error("Failed to get command invocation", e)),
+        };
+
+        match resp.status() {
+            None
+            | Some(CommandInvocationStatus::Pending)
+            | Some(CommandInvocationStatus::InProgress)
+            | Some(CommandInvocationStatus::Delayed)
+            | Some(CommandInvocationStatus::Cancelling) => Ok(CommandPoll::Running),
+            // SSM reports -1 when the script never ran (delivery timeout, cancelled)
+            Some(_) => Ok(CommandPoll::Finished(CommandOutput {
+                exit_code: resp.response_code(),
+                stdout: resp.standard_output_content().unwrap_or_default().to_string(),
+                stderr: resp.standard_error_content().unwrap_or_default().to_string(),
+            })),
+        }
+    }
+
+    async fn cancel(&self, instance: &Instance, handle: &str) -> ComputeResult<()> {
+        self.ssm_client
+            .cancel_command()
+            .command_id(handle)
+            .instance_ids(&instance.instance_id)
+            .send()
+            .await
+            .map_err(|e| aws_error("Failed to cancel command", e))?;
+        Ok(())
+    }
+}
+
+fn aws_error<E: ProvideErrorMetadata + std::fmt::Display>(context: &str, e: E) -> ComputeError {
+    ComputeError::from_aws_code(e.code(), format!("{}: {}", context, e))
+}
+
+// Function secrets live in Secrets Manager under their native names
+#[async_trait]
+impl NativeSecretStore for AwsProvider {
+    async fn put_secret(&self, name: &str, value: &SecretString) -> ComputeResult<()> {
+        let put = self.secrets_client.put_secret_value().secret_id(name).secret_string(value.expose()).send().await;
+        match put {
+            Ok(_) => Ok(()),
+            Err(e) if e.code() == Some("ResourceNotFoundException") => {
+                self.secrets_client
+                    .create_secret()
+                    .name(name)
+                    .secret_string(value.expose())
+                    .send()
+                    .await
+                    .map_err(|e| aws_error("Failed to create function secret", e))?;
+                Ok(())
+            }
+            Err(e) => Err(aws_error("Failed to store function secret", e)),
+        }
+    }
+}
+
 // Private helper methods
 impl AwsProvider {
     async fn create_vpc(&self) -> ComputeResult<String> {
@@ -520,9 +902,94 @@ impl AwsProvider {
         unimplemented!()
     }
 
+    // Moves the alias to `version` and sets or clears provisioned concurrency on it
+    async fn point_alias(&self, config: &FunctionConfig, version: &str) -> ComputeResult<()> {
+        let updated = self.lambda_client
+            .update_alias()
+            .function_name(&config.name)
+            .name(LAMBDA_ALIAS)
+            .function_version(version)
+            .send()
+            .await;
+        match updated {
+            Ok(_) => {}
+            Err(e) if e.code() == Some("ResourceNotFoundException") => {
+                self.lambda_client
+                    .create_alias()
+                    .function_name(&config.name)
+                    .name(LAMBDA_ALIAS)
+                    .function_version(version)
+                    .send()
+                    .await
+                    .map_err(|e| aws_error("Failed to create function alias", e))?;
+            }
+            Err(e) => return Err(aws_error("Failed to update function alias", e)),
+        }
+
+        match concurrency::native_concurrency(&ProviderType::Aws, config)? {
+            Some(NativeConcurrency::LambdaProvisioned { executions }) => {
+                self.lambda_client
+                    .put_provisioned_concurrency_config()
+                    .function_name(&config.name)
+                    .qualifier(LAMBDA_ALIAS)
+                    .provisioned_concurrent_executions(executions)
+                    .send()
+                    .await
+                    .map_err(|e| aws_error("Failed to set provisioned concurrency", e))?;
+            }
+            _ => {
+                let deleted = self.lambda_client
+                    .delete_provisioned_concurrency_config()
+                    .function_name(&config.name)
+                    .qualifier(LAMBDA_ALIAS)
+                    .send()
+                    .await;
+                match deleted {
+                    Ok(_) => {}
+                    Err(e) if e.code() == Some("ProvisionedConcurrencyConfigNotFoundException") => {}
+                    Err(e) => return Err(aws_error("Failed to remove provisioned concurrency", e)),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Copies referenced secrets into Secrets Manager; the variables only name them
+    async fn function_environment(
+        &self,
+        environment: &HashMap<String, String>,
+    ) -> ComputeResult<aws_sdk_lambda::types::Environment> {
+        let environment = SecretEnvironment::parse(environment)?;
+        let vault = self.config.secrets.as_ref().map(|broker| broker.0.as_ref());
+        secrets::sync_secrets(&environment, vault, self).await?;
+        Ok(aws_sdk_lambda::types::Environment::builder()
+            .set_variables(Some(secrets::lambda_variables(&environment)))
+            .build())
+    }
+
     fn convert_to_function(&self, aws_function: &aws_sdk_lambda::types::FunctionConfiguration) -> ComputeResult<Function> {
-        // Implementation for converting AWS Lambda function to our Function type
-        unimplemented!()
+        let variables = aws_function.environment().and_then(|e| e.variables()).cloned().unwrap_or_default();
+        let state = match aws_function.state() {
+            Some(aws_sdk_lambda::types::State::Active) => FunctionState::Active,
+            Some(aws_sdk_lambda::types::State::Inactive) => FunctionState::Inactive,
+            Some(aws_sdk_lambda::types::State::Failed) => FunctionState::Failed,
+            _ => FunctionState::Pending,
+        };
+        Ok(Function {
+            name: aws_function.function_name().unwrap_or_default().to_string(),
+            runtime: aws_function.runtime().map(|r| r.as_str().to_string()).unwrap_or_default(),
+            handler: aws_function.handler().unwrap_or_default().to_string(),
+            description: aws_function.description().map(str::to_string),
+            memory_mb: aws_function.memory_size().unwrap_or_default(),
+            timeout_sec: aws_function.timeout().unwrap_or_default(),
+            // Pointers at our Secrets Manager copies come back as the references they were made from
+            environment: secrets::from_lambda_variables(&variables).to_environment(),
+            labels: HashMap::new(),
+            annotations: HashMap::new(),
+            state,
+            metrics: self.invocations.metrics(aws_function.function_name().unwrap_or_default()),
+            vpc_config: None,
+        })
     }
 }
 
@@ -539,7 +1006,7 @@ mod tests {
             region: "us-west-2".to_string(),
             credentials: super::super::Credentials {
                 access_key: Some("test".to_string()),
-                secret_key: Some("test".to_string()),
+                secret_key: Some(sirsi_key_vault::Credential::plaintext("test")),
                 token: None,
                 client_id: None,
                 client_secret: None,
@@ -560,6 +1027,8 @@ mod tests {
                 log_retention_days: 30,
                 alert_webhooks: vec![],
             },
+            secrets: None,
+            secret_store: None,
         };
 
         if let Ok(provider) = AwsProvider::init(config).await {
@@ -9,10 +9,15 @@ use azure_mgmt_compute::{
         VirtualMachineScaleSet,
         VirtualMachineScaleSetVM,
         VirtualMachineSize,
+        VirtualMachineRunCommand,
+        VirtualMachineRunCommandProperties,
+        VirtualMachineRunCommandScriptSource,
+        ExecutionState,
     },
 };
 use azure_mgmt_monitor::MonitorClient;
 use azure_mgmt_network::NetworkClient;
+use azure_security_keyvault::SecretClient;
 use azure_functions::{
     FunctionApp,
     FunctionAppClient,
@@ -24,16 +29,23 @@ use azure_functions::{
 
 use crate::error::{ComputeError, ComputeResult};
 use crate::fleet::{FleetConfig, Instance, InstanceGroup};
+use crate::fleet::command::{CommandBackend, CommandOutput, CommandPoll, RemoteCommand};
+use crate::serverless::concurrency::{self, NativeConcurrency};
+use crate::serverless::secrets::{self, NativeSecretStore, SecretEnvironment};
 use crate::serverless::{Function, FunctionConfig};
 use crate::autoscaling::AutoScalingConfig;
 use crate::optimization::OptimizationStrategy;
 use super::{Provider, ProviderConfig, ProviderType};
+use sirsi_key_vault::SecretString;
+use std::collections::HashMap;
 
 pub struct AzureProvider {
     compute_client: ComputeClient,
     network_client: NetworkClient,
     monitor_client: MonitorClient,
     function_client: FunctionAppClient,
+    // Only when `secret_store` names a vault
+    secret_client: Option<SecretClient>,
     config: ProviderConfig,
     subscription_id: String,
     resource_group: String,
@@ -73,14 +85,23 @@ impl Provider for AzureProvider {
 
         let function_client = FunctionAppClient::new(
             subscription_id,
-            Arc::new(creds),
+            Arc::new(creds.clone()),
         );
 
+        let secret_client = match &config.secret_store {
+            Some(vault) => Some(
+                SecretClient::new(&format!("https://{}.vault.azure.net", vault), Arc::new(creds))
+                    .map_err(|e| ComputeError::Config(format!("Failed to create Key Vault client: {}", e)))?,
+            ),
+            None => None,
+        };
+
         Ok(Box::new(Self {
             compute_client,
             network_client,
             monitor_client,
             function_client,
+            secret_client,
             config,
             subscription_id: subscription_id.to_string(),
             resource_group: resource_group.to_string(),
@@ -263,12 +284,16 @@ impl Provider for AzureProvider {
 
     // Serverless Functions
     async fn create_function(&self, config: FunctionConfig) -> ComputeResult<Function> {
-        let function = self.create_azure_function(&config).await?;
+        let app_settings = self.function_app_settings(&config.environment).await?;
+        let warm = concurrency::native_concurrency(&ProviderType::Azure, &config)?;
+        let function = self.create_azure_function(&config, app_settings, warm).await?;
         self.convert_to_function(&function)
     }
 
     async fn update_function(&self, config: FunctionConfig) -> ComputeResult<Function> {
-        let function = self.update_azure_function(&config).await?;
+        let app_settings = self.function_app_settings(&config.environment).await?;
+        let warm = concurrency::native_concurrency(&ProviderType::Azure, &config)?;
+        let function = self.update_azure_function(&config, app_settings, warm).await?;
         self.convert_to_function(&function)
     }
 
@@ -363,6 +388,87 @@ impl Provider for AzureProvider {
     }
 }
 
+// Managed Run Command: each command is a child resource of the VM that runs asynchronously
+// and keeps its output in the instance view until deleted
+#[async_trait]
+impl CommandBackend for AzureProvider {
+    async fn list_instances(&self, fleet_id: &str, group_id: &str) -> ComputeResult<Vec<Instance>> {
+        Provider::list_instances(self, fleet_id, Some(group_id)).await
+    }
+
+    async fn start(&self, instance: &Instance, command: &RemoteCommand) -> ComputeResult<String> {
+        let name = format!("sirsi-{}", uuid::Uuid::new_v4().simple());
+        let run_command = VirtualMachineRunCommand {
+            location: self.config.region.clone(),
+            properties: Some(VirtualMachineRunCommandProperties {
+                source: Some(VirtualMachineRunCommandScriptSource {
+                    script: Some(command.script_in_working_directory()),
+                    ..Default::default()
+                }),
+                async_execution: Some(true),
+                timeout_in_seconds: Some(command.timeout_seconds as i32),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        self.compute_client
+            .virtual_machine_run_commands()
+            .create_or_update(&self.resource_group, &instance.instance_id, &name, run_command)
+            .await
+            .map_err(|e| ComputeError::Provider(format!("Failed to start run command: {}", e)))?;
+        Ok(name)
+    }
+
+    async fn poll(&self, instance: &Instance, handle: &str) -> ComputeResult<CommandPoll> {
+        let run_command = self.compute_client
+            .virtual_machine_run_commands()
+            .get_by_virtual_machine(&self.resource_group, &instance.instance_id, handle)
+            .expand("instanceView")
+            .await
+            .map_err(|e| ComputeError::Provider(format!("Failed to get run command: {}", e)))?;
+
+        let Some(view) = run_command.properties.and_then(|p| p.instance_view) else {
+            return Ok(CommandPoll::Running);
+        };
+        match view.execution_state {
+            None | Some(ExecutionState::Unknown) | Some(ExecutionState::Pending) | Some(ExecutionState::Running) => {
+                Ok(CommandPoll::Running)
+            }
+            Some(_) => Ok(CommandPoll::Finished(CommandOutput {
+                exit_code: view.exit_code.unwrap_or(-1),
+                stdout: view.output.unwrap_or_default(),
+                stderr: view.error.unwrap_or_default(),
+            })),
+        }
+    }
+
+    // Deleting the resource stops a script that is still running
+    async fn cancel(&self, instance: &Instance, handle: &str) -> ComputeResult<()> {
+        self.compute_client
+            .virtual_machine_run_commands()
+            .delete(&self.resource_group, &instance.instance_id, handle)
+            .await
+            .map_err(|e| ComputeError::Provider(format!("Failed to cancel run command: {}", e)))?;
+        Ok(())
+    }
+}
+
+// Function secrets live in the Key Vault named by `secret_store`, under their native names
+#[async_trait]
+impl NativeSecretStore for AzureProvider {
+    async fn put_secret(&self, name: &str, value: &SecretString) -> ComputeResult<()> {
+        let client = self.secret_client
+            .as_ref()
+            .ok_or_else(|| ComputeError::Config("No Key Vault configured for function secrets".into()))?;
+        // Setting a secret that exists adds a new version
+        client
+            .set(name, value.expose())
+            .await
+            .map_err(|e| ComputeError::Provider(format!("Failed to store function secret: {}", e)))?;
+        Ok(())
+    }
+}
+
 // Private helper methods
 impl AzureProvider {
     // VMSS Operations
@@ -387,11 +493,37 @@ impl AzureProvider {
     }
 
     // Azure Functions
-    async fn create_azure_function(&self, config: &FunctionConfig) -> ComputeResult<FunctionEnvelope> {
+    // Copies referenced secrets into the configured Key Vault; the settings only reference them.
+    // The app's managed identity needs get access to the vault's secrets.
+    async fn function_app_settings(&self, environment: &HashMap<String, String>) -> ComputeResult<HashMap<String, String>> {
+        let environment = SecretEnvironment::parse(environment)?;
+        if environment.secrets.is_empty() {
+            return Ok(secrets::azure_app_settings(&environment, ""));
+        }
+        let vault_name = self.config.secret_store.as_deref().ok_or_else(|| {
+            ComputeError::Config("Function secrets on Azure need a Key Vault configured as the secret store".into())
+        })?;
+        let vault = self.config.secrets.as_ref().map(|broker| broker.0.as_ref());
+        secrets::sync_secrets(&environment, vault, self).await?;
+        Ok(secrets::azure_app_settings(&environment, vault_name))
+    }
+
+    // `warm` sets the Premium plan's always-ready and pre-warmed instance counts; `None` resets both
+    async fn create_azure_function(
+        &self,
+        config: &FunctionConfig,
+        app_settings: HashMap<String, String>,
+        warm: Option<NativeConcurrency>,
+    ) -> ComputeResult<FunctionEnvelope> {
         unimplemented!()
     }
 
-    async fn update_azure_function(&self, config: &FunctionConfig) -> ComputeResult<FunctionEnvelope> {
+    async fn update_azure_function(
+        &self,
+        config: &FunctionConfig,
+        app_settings: HashMap<String, String>,
+        warm: Option<NativeConcurrency>,
+    ) -> ComputeResult<FunctionEnvelope> {
         unimplemented!()
     }
 
@@ -493,7 +625,7 @@ mod tests {
             credentials: super::super::Credentials {
                 client_id: Some("test-subscription".to_string()),
                 tenant_id: Some("test-resource-group".to_string()),
-                client_secret: Some("test-secret".to_string()),
+                client_secret: Some(sirsi_key_vault::Credential::plaintext("test-secret")),
                 ..Default::default()
             },
             network_config: super::super::NetworkConfig {
@@ -509,6 +641,8 @@ mod tests {
                 log_retention_days: 30,
                 alert_webhooks: vec![],
             },
+            secrets: None,
+            secret_store: None,
         };
 
         if let Ok(provider) = AzureProvider::init(config).await {
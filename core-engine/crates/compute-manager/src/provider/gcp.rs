@@ -1,9 +1,13 @@
 use async_trait::async_trait;
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use crate::error::{ComputeError, ComputeResult};
 use crate::fleet::{FleetConfig, Instance, InstanceGroup};
-use crate::serverless::{Function, FunctionConfig};
+use crate::fleet::command::{CommandBackend, CommandOutput, CommandPlatform, CommandPoll, RemoteCommand};
+use crate::serverless::concurrency::{self, InvocationLog, NativeConcurrency};
+use crate::serverless::secrets::{self, NativeSecretStore, SecretEnvironment};
+use crate::serverless::{Function, FunctionConfig, FunctionInvocation};
 use crate::autoscaling::AutoScalingConfig;
 use crate::optimization::OptimizationStrategy;
 use super::{Provider, ProviderConfig, ProviderType};
@@ -12,6 +16,10 @@ use google_cloud_compute::client::Client as ComputeClient;
 use google_cloud_run::client::Client as RunClient;
 use google_cloud_autoscaling::client::Client as AutoscalingClient;
 use google_cloud_monitoring::client::Client as MonitoringClient;
+use google_cloud_os_config::client::Client as OsConfigClient;
+use google_cloud_os_config::types::{ExecResource, OsPolicyAssignment, ComplianceState};
+use google_cloud_secretmanager::client::Client as SecretManagerClient;
+use sirsi_key_vault::SecretString;
 use serde_json::Value;
 
 pub struct GcpProvider {
@@ -22,6 +30,9 @@ pub struct GcpProvider {
     run_client: RunClient,
     autoscaling_client: AutoscalingClient,
     monitoring_client: MonitoringClient,
+    os_config_client: OsConfigClient,
+    secret_client: SecretManagerClient,
+    invocations: InvocationLog,
 }
 
 #[async_trait]
@@ -35,8 +46,10 @@ async fn init(config: ProviderConfig) -> ComputeResult<Box<dyn Provider>> {
             .as_ref()
             .ok_or_else(|| ComputeError::Config("GCP project ID required".into()))?;
 
-        let json_key = config.credentials.json_key
+        let resolved = config.resolve_credentials().await?;
+        let json_key = resolved.json_key
             .as_ref()
+            .map(|key| key.expose())
             .ok_or_else(|| ComputeError::Config("GCP JSON key required".into()))?;
 
         // Initialize GCP clients
@@ -64,6 +77,18 @@ async fn init(config: ProviderConfig) -> ComputeResult<Box<dyn Provider>> {
             &config.region,
         ).await.map_err(|e| ComputeError::Provider(format!("Failed to create Monitoring client: {}", e)))?;
 
+        let os_config_client = OsConfigClient::new(
+            project_id,
+            json_key,
+            &config.region,
+        ).await.map_err(|e| ComputeError::Provider(format!("Failed to create OS Config client: {}", e)))?;
+
+        let secret_client = SecretManagerClient::new(
+            project_id,
+            json_key,
+            &config.region,
+        ).await.map_err(|e| ComputeError::Provider(format!("Failed to create Secret Manager client: {}", e)))?;
+
         Ok(Box::new(Self {
             project_id: project_id.clone(),
             region: config.region.clone(),
@@ -72,6 +97,9 @@ async fn init(config: ProviderConfig) -> ComputeResult<Box<dyn Provider>> {
             run_client,
             autoscaling_client,
             monitoring_client,
+            os_config_client,
+            secret_client,
+            invocations: InvocationLog::default(),
         }))
     }
 
@@ -343,13 +371,16 @@ async fn init(config: ProviderConfig) -> ComputeResult<Box<dyn Provider>> {
 
     // Serverless Functions
     async fn create_function(&self, config: FunctionConfig) -> ComputeResult<Function> {
+        let env = self.function_env(&config.environment).await?;
+        let min_instances = Self::min_instances(&config)?;
         // Create Cloud Run service
         let service = self.run_client
             .create_service(&config.name)
             .image(format!("gcr.io/{}/function-{}", self.project_id, config.name))
             .memory(format!("{}Mi", config.memory_mb))
             .timeout(config.timeout_sec)
-            .env_vars(config.environment)
+            .env(env)
+            .min_instances(min_instances)
             .vpc_connector(config.vpc_config.as_ref().map(|vpc| vpc.subnet_ids.join(",")))
             .await
             .map_err(|e| ComputeError::Provider(format!("Failed to create Cloud Run service: {}", e)))?;
@@ -366,18 +397,21 @@ async fn init(config: ProviderConfig) -> ComputeResult<Box<dyn Provider>> {
             labels: service.labels().clone(),
             annotations: service.annotations().clone(),
             state: super::serverless::FunctionState::Pending,
-            metrics: None,
+            metrics: self.invocations.metrics(service.name()),
             vpc_config: config.vpc_config,
         })
     }
 
     async fn update_function(&self, config: FunctionConfig) -> ComputeResult<Function> {
+        let env = self.function_env(&config.environment).await?;
+        let min_instances = Self::min_instances(&config)?;
         let service = self.run_client
             .update_service(&config.name)
             .image(format!("gcr.io/{}/function-{}", self.project_id, config.name))
             .memory(format!("{}Mi", config.memory_mb))
             .timeout(config.timeout_sec)
-            .env_vars(config.environment)
+            .env(env)
+            .min_instances(min_instances)
             .vpc_connector(config.vpc_config.as_ref().map(|vpc| vpc.subnet_ids.join(",")))
             .await
             .map_err(|e| ComputeError::Provider(format!("Failed to update Cloud Run service: {}", e)))?;
@@ -393,7 +427,7 @@ async fn init(config: ProviderConfig) -> ComputeResult<Box<dyn Provider>> {
             labels: service.labels().clone(),
             annotations: service.annotations().clone(),
             state: super::serverless::FunctionState::Pending,
-            metrics: None,
+            metrics: self.invocations.metrics(service.name()),
             vpc_config: config.vpc_config,
         })
     }
@@ -415,11 +449,26 @@ async fn init(config: ProviderConfig) -> ComputeResult<Box<dyn Provider>> {
     }
 
     async fn invoke_function(&self, function_id: &str, payload: Vec<u8>) -> ComputeResult<Vec<u8>> {
+        let start_time = chrono::Utc::now();
         let response = self.run_client
             .invoke_service(function_id)
             .payload(payload)
-            .await
-            .map_err(|e| ComputeError::Provider(format!("Failed to invoke function: {}", e)))?;
+            .await;
+        let end_time = chrono::Utc::now();
+        // Cloud Run doesn't report cold starts per request; metrics infer them from latency
+        self.invocations.record(FunctionInvocation {
+            function_name: function_id.to_string(),
+            request_id: uuid::Uuid::new_v4().to_string(),
+            start_time,
+            end_time: Some(end_time),
+            duration_ms: Some((end_time - start_time).num_milliseconds()),
+            memory_used_mb: None,
+            error: response.as_ref().err().map(ToString::to_string),
+            logs: Vec::new(),
+            cold_start: None,
+            init_duration_ms: None,
+        });
+        let response = response.map_err(|e| ComputeError::Provider(format!("Failed to invoke function: {}", e)))?;
 
         Ok(response.body().to_vec())
     }
@@ -580,6 +629,116 @@ async fn init(config: ProviderConfig) -> ComputeResult<Box<dyn Provider>> {
     }
 }
 
+// GCE has no ad-hoc run API, so each command is a one-off OS policy assignment with a single
+// exec resource scoped to the instance. The agent reports at most 100 KiB of output per run.
+#[async_trait]
+impl CommandBackend for GcpProvider {
+    async fn list_instances(&self, fleet_id: &str, group_id: &str) -> ComputeResult<Vec<Instance>> {
+        Provider::list_instances(self, fleet_id, Some(group_id)).await
+    }
+
+    async fn start(&self, instance: &Instance, command: &RemoteCommand) -> ComputeResult<String> {
+        let zone = instance_zone(instance)?;
+        let name = format!("sirsi-{}", uuid::Uuid::new_v4().simple());
+        let exec = match command.platform {
+            CommandPlatform::Linux => ExecResource::shell(command.script_in_working_directory()),
+            CommandPlatform::Windows => ExecResource::powershell(command.script_in_working_directory()),
+        };
+        let assignment = OsPolicyAssignment::one_off(&name)
+            .instance_names(vec![instance.instance_id.clone()])
+            .exec_resource(exec.timeout_seconds(command.timeout_seconds));
+
+        self.os_config_client
+            .os_policy_assignments()
+            .create(&self.project_id, zone, &name, assignment)
+            .await
+            .map_err(|e| ComputeError::Provider(format!("Failed to create OS policy assignment: {}", e)))?;
+        Ok(name)
+    }
+
+    async fn poll(&self, instance: &Instance, handle: &str) -> ComputeResult<CommandPoll> {
+        let zone = instance_zone(instance)?;
+        let report = self.os_config_client
+            .os_policy_assignment_reports()
+            .get(&self.project_id, zone, &instance.instance_id, handle)
+            .await
+            .map_err(|e| ComputeError::Provider(format!("Failed to get OS policy report: {}", e)))?;
+
+        let Some(result) = report.exec_results().first() else {
+            return Ok(CommandPoll::Running);
+        };
+        match result.compliance_state() {
+            ComplianceState::Unknown => Ok(CommandPoll::Running),
+            state => Ok(CommandPoll::Finished(CommandOutput {
+                exit_code: result.exit_code().unwrap_or(if state == ComplianceState::Compliant { 0 } else { 1 }),
+                stdout: result.output().to_string(),
+                stderr: result.error_message().to_string(),
+            })),
+        }
+    }
+
+    async fn cancel(&self, instance: &Instance, handle: &str) -> ComputeResult<()> {
+        let zone = instance_zone(instance)?;
+        self.os_config_client
+            .os_policy_assignments()
+            .delete(&self.project_id, zone, handle)
+            .await
+            .map_err(|e| ComputeError::Provider(format!("Failed to delete OS policy assignment: {}", e)))?;
+        Ok(())
+    }
+}
+
+// Function secrets live in Secret Manager under their native names. The Cloud Run service
+// account needs roles/secretmanager.secretAccessor on them.
+#[async_trait]
+impl NativeSecretStore for GcpProvider {
+    async fn put_secret(&self, name: &str, value: &SecretString) -> ComputeResult<()> {
+        let exists = self.secret_client
+            .get_secret(&self.project_id, name)
+            .await
+            .map_err(|e| ComputeError::Provider(format!("Failed to look up function secret: {}", e)))?
+            .is_some();
+        if !exists {
+            self.secret_client
+                .create_secret(&self.project_id, name)
+                .replication_automatic()
+                .await
+                .map_err(|e| ComputeError::Provider(format!("Failed to create function secret: {}", e)))?;
+        }
+        self.secret_client
+            .add_secret_version(&self.project_id, name)
+            .payload(value.expose().as_bytes().to_vec())
+            .await
+            .map_err(|e| ComputeError::Provider(format!("Failed to store function secret: {}", e)))?;
+        Ok(())
+    }
+}
+
+impl GcpProvider {
+    // Copies referenced secrets into Secret Manager; the service env only references them
+    async fn function_env(&self, environment: &HashMap<String, String>) -> ComputeResult<Vec<secrets::CloudRunEnvVar>> {
+        let environment = SecretEnvironment::parse(environment)?;
+        let vault = self.config.secrets.as_ref().map(|broker| broker.0.as_ref());
+        secrets::sync_secrets(&environment, vault, self).await?;
+        Ok(secrets::cloud_run_env(&environment))
+    }
+
+    // Warm instances stand in for provisioned concurrency; 0 lets the service scale to zero
+    fn min_instances(config: &FunctionConfig) -> ComputeResult<i32> {
+        Ok(match concurrency::native_concurrency(&ProviderType::Gcp, config)? {
+            Some(NativeConcurrency::CloudRunMinInstances { min_instances }) => min_instances,
+            _ => 0,
+        })
+    }
+}
+
+fn instance_zone(instance: &Instance) -> ComputeResult<&str> {
+    instance.labels
+        .get("zone")
+        .map(String::as_str)
+        .ok_or_else(|| ComputeError::Validation(format!("Instance {} has no zone label", instance.instance_id)))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -590,7 +749,7 @@ mod tests {
             region: "us-central1".to_string(),
             credentials: super::super::Credentials {
                 project_id: Some("test-project".to_string()),
-                json_key: Some("test-key".to_string()),
+                json_key: Some(sirsi_key_vault::Credential::plaintext("test-key")),
                 ..Default::default()
             },
             network_config: super::super::NetworkConfig {
@@ -606,6 +765,8 @@ mod tests {
                 log_retention_days: 30,
                 alert_webhooks: vec![],
             },
+            secrets: None,
+            secret_store: None,
         };
 
         if let Ok(provider) = GcpProvider::init(config).await {
@@ -12,8 +12,12 @@ use crate::serverless::{Function, FunctionConfig};
 use crate::autoscaling::AutoScalingConfig;
 use crate::optimization::OptimizationStrategy;
 
+pub use crate::credentials::{Credentials, ResolvedCredentials, SecretBroker};
+
 use serde::{Deserialize, Serialize};
+use sirsi_key_vault::secret::SecretManager;
 use std::collections::HashMap;
+use std::sync::Arc;
 
 use async_trait::async_trait;
 use std::fmt::Debug;
@@ -25,6 +29,12 @@ pub struct ProviderConfig {
     pub credentials: Credentials,
     pub network_config: NetworkConfig,
     pub monitoring_config: MonitoringConfig,
+    #[serde(skip)]
+    pub secrets: Option<SecretBroker>,
+    // Azure Key Vault that function secrets are copied into; AWS and GCP use the account's
+    // Secrets Manager or Secret Manager
+    #[serde(default)]
+    pub secret_store: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -35,18 +45,6 @@ pub enum ProviderType {
     Gcp,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
-pub struct Credentials {
-    pub access_key: Option<String>,
-    pub secret_key: Option<String>,
-    pub token: Option<String>,
-    pub client_id: Option<String>,
-    pub client_secret: Option<String>,
-    pub tenant_id: Option<String>,
-    pub project_id: Option<String>,
-    pub json_key: Option<String>,
-}
-
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NetworkConfig {
     pub vpc_id: Option<String>,
@@ -67,7 +65,9 @@ pub struct MonitoringConfig {
 #[async_trait]
 pub trait Provider: Send + Sync + 'static {
     // Initialization
-    async fn init(config: ProviderConfig) -> ComputeResult<Box<dyn Provider>>;
+    async fn init(config: ProviderConfig) -> ComputeResult<Box<dyn Provider>>
+    where
+        Self: Sized;
 
     // Fleet Management
     async fn create_fleet(&self, config: FleetConfig) -> ComputeResult<FleetConfig>;
@@ -135,6 +135,8 @@ impl ProviderConfig {
                 log_retention_days: 30,
                 alert_webhooks: Vec::new(),
             },
+            secrets: None,
+            secret_store: None,
         }
     }
 
@@ -143,6 +145,22 @@ impl ProviderConfig {
         self
     }
 
+    pub fn with_secret_manager(mut self, secrets: Arc<dyn SecretManager>) -> Self {
+        self.secrets = Some(SecretBroker(secrets));
+        self
+    }
+
+    pub fn with_secret_store(mut self, secret_store: impl Into<String>) -> Self {
+        self.secret_store = Some(secret_store.into());
+        self
+    }
+
+    // Looks secrets up afresh on every call; providers call this once from `init`
+    pub async fn resolve_credentials(&self) -> ComputeResult<ResolvedCredentials> {
+        let secrets = self.secrets.as_ref().map(|broker| broker.0.as_ref());
+        self.credentials.resolve(secrets).await
+    }
+
     pub fn with_network_config(mut self, network_config: NetworkConfig) -> Self {
         self.network_config = network_config;
         self
@@ -166,7 +184,7 @@ mod tests {
         )
         .with_credentials(Credentials {
             access_key: Some("test-key".to_string()),
-            secret_key: Some("test-secret".to_string()),
+            secret_key: Some(sirsi_key_vault::Credential::plaintext("test-secret")),
             ..Default::default()
         })
         .with_network_config(NetworkConfig {
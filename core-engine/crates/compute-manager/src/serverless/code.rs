@@ -0,0 +1,272 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::stream::{BoxStream, StreamExt};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sirsi_common::Retryable;
+use sirsi_object_store::{ObjectStore, PutOptions, StoreError, Uploader};
+use tokio::io::AsyncWriteExt;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::error::{ComputeError, ComputeResult};
+
+const FILE_SCHEME: &str = "file://";
+const S3_SCHEME: &str = "s3://";
+
+// Reference to a deployment package uploaded ahead of the deploy
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CodeRef {
+    // `sha256:<hex>` of the package bytes
+    pub digest: String,
+    // `file://` path, or the object store URL such as `s3://bucket/key`
+    pub location: String,
+    pub size_bytes: u64,
+}
+
+impl CodeRef {
+    // Bucket and key, for providers that deploy straight from object storage
+    pub fn s3_location(&self) -> Option<(&str, &str)> {
+        self.location.strip_prefix(S3_SCHEME)?.split_once('/')
+    }
+
+    // Reads a locally stored package back and checks it still matches its digest
+    pub async fn load(&self) -> ComputeResult<Vec<u8>> {
+        let path = self.location.strip_prefix(FILE_SCHEME).ok_or_else(|| {
+            ComputeError::Config(format!("Code at {} has to be deployed from object storage", self.location))
+        })?;
+        let code = tokio::fs::read(path)
+            .await
+            .map_err(|e| ComputeError::NotFound(format!("Function code {}: {}", self.location, e)))?;
+        let digest = sha256_digest(&code);
+        if digest != self.digest {
+            return Err(ComputeError::Validation(format!(
+                "Function code {} has digest {}, expected {}",
+                self.location, digest, self.digest
+            )));
+        }
+        Ok(code)
+    }
+}
+
+pub fn sha256_digest(bytes: &[u8]) -> String {
+    format!("sha256:{:x}", Sha256::digest(bytes))
+}
+
+#[async_trait]
+pub trait CodeStore: Send + Sync {
+    // Consumes the chunks as they arrive, failing once more than `max_bytes` have been read
+    async fn put(
+        &self,
+        function: &str,
+        chunks: BoxStream<'_, ComputeResult<Bytes>>,
+        max_bytes: u64,
+    ) -> ComputeResult<CodeRef>;
+}
+
+// Packages on local disk, named by digest so re-uploading identical code is a no-op
+pub struct FileCodeStore {
+    root: PathBuf,
+}
+
+impl FileCodeStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    async fn spool(
+        file: &mut tokio::fs::File,
+        mut chunks: BoxStream<'_, ComputeResult<Bytes>>,
+        max_bytes: u64,
+    ) -> ComputeResult<(String, u64)> {
+        let mut hasher = Sha256::new();
+        let mut size = 0u64;
+        while let Some(chunk) = chunks.next().await {
+            let chunk = chunk?;
+            size += chunk.len() as u64;
+            if size > max_bytes {
+                return Err(ComputeError::TooLarge(format!("Function code exceeds {} bytes", max_bytes)));
+            }
+            hasher.update(&chunk);
+            file.write_all(&chunk).await.map_err(io_error)?;
+        }
+        file.flush().await.map_err(io_error)?;
+        Ok((format!("sha256:{:x}", hasher.finalize()), size))
+    }
+}
+
+fn io_error(e: std::io::Error) -> ComputeError {
+    ComputeError::Internal(format!("Failed to write function code: {}", e))
+}
+
+#[async_trait]
+impl CodeStore for FileCodeStore {
+    async fn put(
+        &self,
+        function: &str,
+        chunks: BoxStream<'_, ComputeResult<Bytes>>,
+        max_bytes: u64,
+    ) -> ComputeResult<CodeRef> {
+        let dir = self.root.join(function);
+        tokio::fs::create_dir_all(&dir).await.map_err(io_error)?;
+        let partial = dir.join(format!(".upload-{}", Uuid::new_v4()));
+        let mut file = tokio::fs::File::create(&partial).await.map_err(io_error)?;
+
+        let (digest, size) = match Self::spool(&mut file, chunks, max_bytes).await {
+            Ok(written) => written,
+            Err(e) => {
+                drop(file);
+                remove_partial(&partial).await;
+                return Err(e);
+            }
+        };
+        drop(file);
+
+        let path = dir.join(format!("{}.zip", digest.trim_start_matches("sha256:")));
+        if let Err(e) = tokio::fs::rename(&partial, &path).await {
+            remove_partial(&partial).await;
+            return Err(io_error(e));
+        }
+        info!("Stored {} bytes of code for {} as {}", size, function, digest);
+        Ok(CodeRef {
+            digest,
+            location: format!("{}{}", FILE_SCHEME, path.display()),
+            size_bytes: size,
+        })
+    }
+}
+
+// Packages in object storage, uploaded in parts as the chunks arrive so large packages never sit
+// in memory whole. Keys are unique per upload since the digest is only known at the end.
+pub struct ObjectCodeStore {
+    store: Arc<dyn ObjectStore>,
+    prefix: String,
+}
+
+#[derive(Default)]
+struct Tally {
+    hasher: Sha256,
+    size: u64,
+    failed: Option<ComputeError>,
+}
+
+impl ObjectCodeStore {
+    pub fn new(store: Arc<dyn ObjectStore>) -> Self {
+        Self { store, prefix: "functions".into() }
+    }
+
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into().trim_end_matches('/').to_string();
+        self
+    }
+}
+
+#[async_trait]
+impl CodeStore for ObjectCodeStore {
+    async fn put(
+        &self,
+        function: &str,
+        chunks: BoxStream<'_, ComputeResult<Bytes>>,
+        max_bytes: u64,
+    ) -> ComputeResult<CodeRef> {
+        let key = format!("{}/{}/{}.zip", self.prefix, function, Uuid::new_v4());
+        let tally = Mutex::new(Tally::default());
+        // The uploader only sees store errors, so the real cause is kept aside in the tally
+        let counted = chunks
+            .map(|chunk| {
+                let mut tally = tally.lock().unwrap();
+                let chunk = match chunk {
+                    Ok(chunk) => chunk,
+                    Err(e) => {
+                        tally.failed = Some(e);
+                        return Err(StoreError::Invalid("Function code stream failed".into()));
+                    }
+                };
+                tally.size += chunk.len() as u64;
+                if tally.size > max_bytes {
+                    tally.failed = Some(ComputeError::TooLarge(format!("Function code exceeds {} bytes", max_bytes)));
+                    return Err(StoreError::Invalid("Function code too large".into()));
+                }
+                tally.hasher.update(&chunk);
+                Ok(chunk)
+            })
+            .boxed();
+
+        let options = PutOptions::new().with_content_type("application/zip");
+        let result = Uploader::new(self.store.clone()).upload(&key, counted, &options).await;
+        let tally = tally.into_inner().unwrap();
+        if let Some(e) = tally.failed {
+            return Err(e);
+        }
+        result.map_err(|e| ComputeError::from_kind(e.kind(), format!("Failed to store code for {}: {}", function, e)))?;
+
+        let digest = format!("sha256:{:x}", tally.hasher.finalize());
+        info!("Stored {} bytes of code for {} as {}", tally.size, function, digest);
+        Ok(CodeRef {
+            digest,
+            location: self.store.url(&key),
+            size_bytes: tally.size,
+        })
+    }
+}
+
+async fn remove_partial(path: &Path) {
+    if let Err(e) = tokio::fs::remove_file(path).await {
+        warn!("Failed to remove partial upload {}: {}", path.display(), e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream;
+
+    fn chunked(bytes: &[u8], chunk_size: usize) -> BoxStream<'static, ComputeResult<Bytes>> {
+        let chunks: Vec<ComputeResult<Bytes>> =
+            bytes.chunks(chunk_size).map(|c| Ok(Bytes::copy_from_slice(c))).collect();
+        stream::iter(chunks).boxed()
+    }
+
+    #[tokio::test]
+    async fn test_chunked_upload_digest_and_limit() {
+        let root = tempfile::tempdir().unwrap();
+        let store = FileCodeStore::new(root.path());
+        // 6MB of non-repeating-ish bytes, sent in 64KB chunks
+        let package: Vec<u8> = (0..6 * 1024 * 1024u32).map(|i| (i.wrapping_mul(2654435761) >> 24) as u8).collect();
+
+        let code_ref = store.put("resize-images", chunked(&package, 64 * 1024), 8 * 1024 * 1024).await.unwrap();
+        assert_eq!(code_ref.size_bytes, package.len() as u64);
+        assert_eq!(code_ref.digest, sha256_digest(&package));
+        assert!(code_ref.s3_location().is_none());
+        assert_eq!(code_ref.load().await.unwrap(), package);
+
+        let err = store.put("resize-images", chunked(&package, 64 * 1024), 1024 * 1024).await.unwrap_err();
+        assert!(matches!(err, ComputeError::TooLarge(_)));
+        // Neither the rejected upload nor the accepted one's temp file is left behind
+        let entries = std::fs::read_dir(root.path().join("resize-images")).unwrap().count();
+        assert_eq!(entries, 1);
+
+        let tampered = CodeRef { digest: sha256_digest(b"other"), ..code_ref };
+        assert!(matches!(tampered.load().await, Err(ComputeError::Validation(_))));
+    }
+
+    #[tokio::test]
+    async fn test_object_store_upload_digest_and_limit() {
+        let root = tempfile::tempdir().unwrap();
+        let objects: Arc<dyn ObjectStore> = Arc::new(sirsi_object_store::LocalFileStore::new(root.path()));
+        let store = ObjectCodeStore::new(objects.clone());
+        let package: Vec<u8> = (0..6 * 1024 * 1024u32).map(|i| (i.wrapping_mul(2654435761) >> 24) as u8).collect();
+
+        let code_ref = store.put("resize-images", chunked(&package, 64 * 1024), 8 * 1024 * 1024).await.unwrap();
+        assert_eq!(code_ref.size_bytes, package.len() as u64);
+        assert_eq!(code_ref.digest, sha256_digest(&package));
+        assert_eq!(code_ref.load().await.unwrap(), package);
+
+        let err = store.put("resize-images", chunked(&package, 64 * 1024), 1024 * 1024).await.unwrap_err();
+        assert!(matches!(err, ComputeError::TooLarge(_)));
+        assert_eq!(objects.list("functions/resize-images/").await.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,322 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{FunctionConfig, FunctionInvocation, FunctionMetrics};
+use crate::error::{ComputeError, ComputeResult};
+use crate::provider::ProviderType;
+
+// Lambda's default regional concurrency quota
+pub const MAX_PROVISIONED_CONCURRENCY: i32 = 1000;
+// Requests one Cloud Run instance takes at once unless the service says otherwise
+pub const CLOUD_RUN_DEFAULT_CONCURRENCY: i32 = 80;
+// host.json's default `maxConcurrentRequests` for HTTP triggers on a Premium plan instance
+pub const AZURE_PREMIUM_CONCURRENCY: i32 = 100;
+
+// Without a provider signal an invocation counts as cold when it ran this many times longer
+// than the function's typical warm duration, and by at least COLD_START_MIN_EXTRA_MS
+const COLD_START_FACTOR: i64 = 3;
+const COLD_START_MIN_EXTRA_MS: i64 = 250;
+
+// `provisioned_concurrency` in each backend's own terms
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NativeConcurrency {
+    // Provisioned concurrency on the version published by the deploy
+    LambdaProvisioned { executions: i32 },
+    CloudRunMinInstances { min_instances: i32 },
+    // Always-ready instances on the Premium plan, with one pre-warmed beyond them so a scale-out
+    // also lands on an initialised instance
+    AzurePremium { always_ready_instances: i32, pre_warmed_instances: i32 },
+}
+
+// `None` when nothing is to be kept warm, which deploys treat as removing any earlier setting
+pub fn native_concurrency(provider: &ProviderType, config: &FunctionConfig) -> ComputeResult<Option<NativeConcurrency>> {
+    let executions = match config.provisioned_concurrency {
+        None | Some(0) => return Ok(None),
+        Some(n) if !(1..=MAX_PROVISIONED_CONCURRENCY).contains(&n) => {
+            return Err(ComputeError::Validation(format!(
+                "Provisioned concurrency {} for {} is outside 1-{}",
+                n, config.name, MAX_PROVISIONED_CONCURRENCY
+            )));
+        }
+        Some(n) => n,
+    };
+    let instances = |per_instance: i32| (executions + per_instance - 1) / per_instance;
+    Ok(Some(match provider {
+        ProviderType::Aws => NativeConcurrency::LambdaProvisioned { executions },
+        ProviderType::Gcp => NativeConcurrency::CloudRunMinInstances { min_instances: instances(CLOUD_RUN_DEFAULT_CONCURRENCY) },
+        ProviderType::Azure => NativeConcurrency::AzurePremium {
+            always_ready_instances: instances(AZURE_PREMIUM_CONCURRENCY),
+            pre_warmed_instances: 1,
+        },
+    }))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LambdaReport {
+    pub duration_ms: Option<i64>,
+    pub cold_start: Option<bool>,
+    pub init_duration_ms: Option<i64>,
+}
+
+fn report_ms(field: &str) -> Option<i64> {
+    field.trim().strip_suffix("ms")?.trim().parse::<f64>().ok().map(|ms| ms.ceil() as i64)
+}
+
+// Reads the REPORT line from the log tail Lambda returns with `LogType::Tail`. Only a cold
+// start carries `Init Duration`; without a REPORT line nothing is known.
+pub fn parse_lambda_report(log_tail: &str) -> LambdaReport {
+    let Some(report) = log_tail.lines().rev().find(|line| line.starts_with("REPORT ")) else {
+        return LambdaReport::default();
+    };
+    let mut parsed = LambdaReport { cold_start: Some(false), ..LambdaReport::default() };
+    for field in report.split('\t') {
+        if let Some(value) = field.trim().strip_prefix("Init Duration:") {
+            parsed.cold_start = Some(true);
+            parsed.init_duration_ms = report_ms(value);
+        } else if let Some(value) = field.trim().strip_prefix("Duration:") {
+            parsed.duration_ms = report_ms(value);
+        }
+    }
+    parsed
+}
+
+fn percentile(sorted: &[i64], p: f64) -> Option<i64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = ((p * sorted.len() as f64).ceil() as usize).clamp(1, sorted.len());
+    Some(sorted[rank - 1])
+}
+
+// Fills in `cold_start` and `init_duration_ms` where the provider said nothing, by comparing
+// each duration with the median of known-warm invocations (or of all of them, if none are)
+pub fn infer_cold_starts(invocations: &mut [FunctionInvocation]) {
+    let mut warm: Vec<i64> = invocations.iter().filter(|i| i.cold_start == Some(false)).filter_map(|i| i.duration_ms).collect();
+    if warm.is_empty() {
+        warm = invocations.iter().filter_map(|i| i.duration_ms).collect();
+    }
+    warm.sort_unstable();
+    let Some(baseline) = percentile(&warm, 0.5) else {
+        return;
+    };
+    for invocation in invocations.iter_mut().filter(|i| i.cold_start.is_none()) {
+        let Some(duration) = invocation.duration_ms else {
+            continue;
+        };
+        let extra = duration - baseline;
+        let cold = duration > baseline * COLD_START_FACTOR && extra >= COLD_START_MIN_EXTRA_MS;
+        invocation.cold_start = Some(cold);
+        if cold {
+            invocation.init_duration_ms = Some(extra);
+        }
+    }
+}
+
+// Most invocations in flight at once
+fn peak_concurrency(invocations: &[FunctionInvocation]) -> i32 {
+    let mut edges: Vec<(DateTime<Utc>, i32)> = Vec::new();
+    for invocation in invocations {
+        let end = invocation.end_time.unwrap_or(invocation.start_time);
+        edges.push((invocation.start_time, 1));
+        edges.push((end, -1));
+    }
+    // An invocation ending at the instant another starts frees its slot first
+    edges.sort_unstable();
+    let (mut current, mut peak) = (0, 0);
+    for (_, delta) in edges {
+        current += delta;
+        peak = peak.max(current);
+    }
+    peak
+}
+
+pub fn function_metrics(invocations: &[FunctionInvocation]) -> FunctionMetrics {
+    let mut invocations = invocations.to_vec();
+    infer_cold_starts(&mut invocations);
+    let durations: Vec<i64> = invocations.iter().filter_map(|i| i.duration_ms).collect();
+    let classified = invocations.iter().filter(|i| i.cold_start.is_some()).count();
+    let cold = invocations.iter().filter(|i| i.cold_start == Some(true)).count();
+    let mut init: Vec<i64> = invocations.iter().filter_map(|i| i.init_duration_ms).collect();
+    init.sort_unstable();
+    FunctionMetrics {
+        invocations: invocations.len() as i64,
+        errors: invocations.iter().filter(|i| i.error.is_some()).count() as i64,
+        throttles: 0,
+        duration_ms: if durations.is_empty() { 0 } else { durations.iter().sum::<i64>() / durations.len() as i64 },
+        concurrent_executions: peak_concurrency(&invocations),
+        memory_utilization: 0.0,
+        cold_start_rate: if classified == 0 { 0.0 } else { cold as f64 / classified as f64 },
+        p95_init_duration_ms: percentile(&init, 0.95),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConcurrencyRecommendation {
+    pub provisioned_concurrency: i32,
+    pub peak_concurrency: i32,
+    pub cold_start_rate: f64,
+    pub reason: String,
+}
+
+// Sizes provisioned concurrency to the `percentile` of per-minute peak concurrency over the
+// observed window, idle minutes included, so steady traffic is covered and rare bursts are left
+// to scale normally. Functions that see no cold starts get nothing.
+pub fn recommend_provisioned_concurrency(invocations: &[FunctionInvocation], percentile_of_minutes: f64) -> ConcurrencyRecommendation {
+    let metrics = function_metrics(invocations);
+    let recommendation = |provisioned_concurrency, reason: String| ConcurrencyRecommendation {
+        provisioned_concurrency,
+        peak_concurrency: metrics.concurrent_executions,
+        cold_start_rate: metrics.cold_start_rate,
+        reason,
+    };
+    let minute = Duration::minutes(1);
+    let start = invocations.iter().map(|i| i.start_time).min().and_then(|t| t.duration_trunc(minute).ok());
+    let end = invocations.iter().map(|i| i.end_time.unwrap_or(i.start_time)).max();
+    let (Some(start), Some(end)) = (start, end) else {
+        return recommendation(0, "No invocations observed".to_string());
+    };
+    if metrics.cold_start_rate == 0.0 {
+        return recommendation(0, "No cold starts observed".to_string());
+    }
+    let mut by_minute: HashMap<i64, Vec<FunctionInvocation>> = HashMap::new();
+    for invocation in invocations {
+        let first = (invocation.start_time - start).num_minutes();
+        let last = (invocation.end_time.unwrap_or(invocation.start_time) - start).num_minutes();
+        for bucket in first..=last {
+            by_minute.entry(bucket).or_default().push(invocation.clone());
+        }
+    }
+    let minutes = (end - start).num_minutes() + 1;
+    let mut peaks: Vec<i64> = (0..minutes).map(|m| by_minute.get(&m).map_or(0, |i| peak_concurrency(i) as i64)).collect();
+    peaks.sort_unstable();
+    let level = percentile(&peaks, percentile_of_minutes).unwrap_or(0).min(MAX_PROVISIONED_CONCURRENCY as i64) as i32;
+    let reason = format!(
+        "{:.0}% of {} minutes peaked at {} or fewer concurrent invocations; {:.1}% of invocations started cold",
+        percentile_of_minutes * 100.0,
+        minutes,
+        level,
+        metrics.cold_start_rate * 100.0
+    );
+    recommendation(level, reason)
+}
+
+// Recent invocations per function, oldest first, for providers to compute metrics from
+pub struct InvocationLog {
+    capacity: usize,
+    invocations: Mutex<HashMap<String, VecDeque<FunctionInvocation>>>,
+}
+
+impl Default for InvocationLog {
+    fn default() -> Self {
+        Self::new(1000)
+    }
+}
+
+impl InvocationLog {
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity, invocations: Mutex::new(HashMap::new()) }
+    }
+
+    pub fn record(&self, invocation: FunctionInvocation) {
+        let mut invocations = self.invocations.lock().unwrap();
+        let recent = invocations.entry(invocation.function_name.clone()).or_default();
+        recent.push_back(invocation);
+        while recent.len() > self.capacity {
+            recent.pop_front();
+        }
+    }
+
+    pub fn invocations(&self, function: &str) -> Vec<FunctionInvocation> {
+        self.invocations.lock().unwrap().get(function).map(|i| i.iter().cloned().collect()).unwrap_or_default()
+    }
+
+    // `None` until the function has been invoked
+    pub fn metrics(&self, function: &str) -> Option<FunctionMetrics> {
+        let invocations = self.invocations(function);
+        (!invocations.is_empty()).then(|| function_metrics(&invocations))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn invocation(start_secs: i64, duration_ms: i64, report: &str) -> FunctionInvocation {
+        let start_time = Utc.with_ymd_and_hms(2025, 7, 9, 12, 0, 0).unwrap() + Duration::seconds(start_secs);
+        let parsed = parse_lambda_report(report);
+        FunctionInvocation {
+            function_name: "checkout".into(),
+            request_id: format!("req-{}", start_secs),
+            start_time,
+            end_time: Some(start_time + Duration::milliseconds(duration_ms)),
+            duration_ms: Some(duration_ms),
+            memory_used_mb: None,
+            error: None,
+            logs: Vec::new(),
+            cold_start: parsed.cold_start,
+            init_duration_ms: parsed.init_duration_ms,
+        }
+    }
+
+    #[test]
+    fn test_native_concurrency_per_backend() {
+        let config = FunctionConfig::new("checkout".into(), "nodejs18.x".into(), "index.handler".into(), Vec::new())
+            .with_provisioned_concurrency(170);
+        assert_eq!(
+            native_concurrency(&ProviderType::Aws, &config).unwrap(),
+            Some(NativeConcurrency::LambdaProvisioned { executions: 170 })
+        );
+        assert_eq!(
+            native_concurrency(&ProviderType::Gcp, &config).unwrap(),
+            Some(NativeConcurrency::CloudRunMinInstances { min_instances: 3 })
+        );
+        assert_eq!(
+            native_concurrency(&ProviderType::Azure, &config).unwrap(),
+            Some(NativeConcurrency::AzurePremium { always_ready_instances: 2, pre_warmed_instances: 1 })
+        );
+        assert_eq!(native_concurrency(&ProviderType::Gcp, &config.clone().with_provisioned_concurrency(0)).unwrap(), None);
+        assert!(native_concurrency(&ProviderType::Aws, &config.with_provisioned_concurrency(-1)).is_err());
+    }
+
+    #[test]
+    fn test_cold_start_metrics_and_recommendation() {
+        let cold = "START RequestId: a\nEND RequestId: a\nREPORT RequestId: a\tDuration: 41.20 ms\tBilled Duration: 42 ms\t\
+                    Memory Size: 512 MB\tMax Memory Used: 88 MB\tInit Duration: 812.37 ms\t";
+        let warm = "REPORT RequestId: b\tDuration: 38.00 ms\tBilled Duration: 38 ms\tMemory Size: 512 MB\tMax Memory Used: 90 MB\t";
+        assert_eq!(parse_lambda_report(cold), LambdaReport { duration_ms: Some(42), cold_start: Some(true), init_duration_ms: Some(813) });
+        assert_eq!(parse_lambda_report(warm).cold_start, Some(false));
+        assert_eq!(parse_lambda_report("no report here"), LambdaReport::default());
+
+        // Lambda says which were cold; the two without a report are judged on duration
+        let mut invocations = vec![
+            invocation(0, 42, cold),
+            invocation(1, 40, warm),
+            invocation(2, 38, warm),
+            invocation(3, 44, warm),
+            invocation(4, 900, ""),
+            invocation(5, 45, ""),
+            invocation(70, 41, cold),
+        ];
+        invocations[1].error = Some("timeout".into());
+        let metrics = function_metrics(&invocations);
+        assert_eq!((metrics.invocations, metrics.errors, metrics.concurrent_executions), (7, 1, 1));
+        assert!((metrics.cold_start_rate - 3.0 / 7.0).abs() < 1e-9);
+        assert_eq!(metrics.p95_init_duration_ms, Some(860));
+
+        let quiet = recommend_provisioned_concurrency(&invocations[1..4], 0.9);
+        assert_eq!((quiet.provisioned_concurrency, quiet.reason.as_str()), (0, "No cold starts observed"));
+
+        // Four overlapping at the top of every minute for ten minutes, with cold starts
+        let busy: Vec<FunctionInvocation> = (0..10)
+            .flat_map(|m| (0..4).map(move |i| invocation(m * 60 + i, 5_000, if i == 3 { cold } else { warm })))
+            .collect();
+        let recommendation = recommend_provisioned_concurrency(&busy, 0.9);
+        assert_eq!((recommendation.provisioned_concurrency, recommendation.peak_concurrency), (4, 4));
+        assert!(recommendation.reason.contains("25.0% of invocations started cold"));
+    }
+}
@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use futures::future::BoxFuture;
+use sirsi_key_vault::secret::SecretManager;
+
+use super::concurrency::InvocationLog;
+use super::mapping::FunctionInvoker;
+use super::secrets::{resolve_secret, SecretEnvironment};
+use super::{FunctionInvocation, FunctionMetrics};
+use crate::error::{ComputeError, ComputeResult};
+
+type Handler = Arc<dyn Fn(Vec<u8>, HashMap<String, String>) -> BoxFuture<'static, ComputeResult<Vec<u8>>> + Send + Sync>;
+
+// Runs functions in-process from registered handlers, for local development and tests.
+// Every call is kept as a `FunctionInvocation` with the payload it received as its only log line.
+// Secret references in a function's environment are resolved on every invocation, so a rotated
+// secret is seen by the next call. A function's first invocation counts as its cold start.
+#[derive(Default)]
+pub struct LocalFunctionRuntime {
+    handlers: HashMap<String, Handler>,
+    environments: HashMap<String, SecretEnvironment>,
+    secrets: Option<Arc<dyn SecretManager>>,
+    invocations: InvocationLog,
+}
+
+impl LocalFunctionRuntime {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_handler<F, Fut>(self, function: impl Into<String>, handler: F) -> Self
+    where
+        F: Fn(Vec<u8>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ComputeResult<Vec<u8>>> + Send + 'static,
+    {
+        self.with_env_handler(function, move |payload, _| handler(payload))
+    }
+
+    // The handler also gets the function's environment, with secrets resolved
+    pub fn with_env_handler<F, Fut>(mut self, function: impl Into<String>, handler: F) -> Self
+    where
+        F: Fn(Vec<u8>, HashMap<String, String>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ComputeResult<Vec<u8>>> + Send + 'static,
+    {
+        self.handlers.insert(function.into(), Arc::new(move |payload, env| Box::pin(handler(payload, env))));
+        self
+    }
+
+    // Takes `FunctionConfig.environment` as is, references included
+    pub fn with_environment(mut self, function: impl Into<String>, environment: &HashMap<String, String>) -> ComputeResult<Self> {
+        self.environments.insert(function.into(), SecretEnvironment::parse(environment)?);
+        Ok(self)
+    }
+
+    pub fn with_secret_manager(mut self, secrets: Arc<dyn SecretManager>) -> Self {
+        self.secrets = Some(secrets);
+        self
+    }
+
+    async fn environment(&self, function: &str) -> ComputeResult<HashMap<String, String>> {
+        let Some(environment) = self.environments.get(function) else {
+            return Ok(HashMap::new());
+        };
+        let mut resolved: HashMap<String, String> = environment.plain.clone().into_iter().collect();
+        for (name, reference) in &environment.secrets {
+            let value = resolve_secret(reference, self.secrets.as_deref()).await?;
+            resolved.insert(name.clone(), value.expose().to_string());
+        }
+        Ok(resolved)
+    }
+
+    // Oldest first
+    pub fn invocations(&self, function: &str) -> Vec<FunctionInvocation> {
+        self.invocations.invocations(function)
+    }
+
+    pub fn metrics(&self, function: &str) -> Option<FunctionMetrics> {
+        self.invocations.metrics(function)
+    }
+}
+
+#[async_trait]
+impl FunctionInvoker for LocalFunctionRuntime {
+    async fn invoke(&self, function: &str, payload: Vec<u8>) -> ComputeResult<Vec<u8>> {
+        let handler = self
+            .handlers
+            .get(function)
+            .cloned()
+            .ok_or_else(|| ComputeError::NotFound(format!("Function {} is not registered locally", function)))?;
+        let cold_start = self.invocations.invocations(function).is_empty();
+        let start_time = Utc::now();
+        let log = String::from_utf8_lossy(&payload).into_owned();
+        let result = match self.environment(function).await {
+            Ok(environment) => handler(payload, environment).await,
+            Err(e) => Err(e),
+        };
+        let end_time = Utc::now();
+        self.invocations.record(FunctionInvocation {
+            function_name: function.to_string(),
+            request_id: uuid::Uuid::new_v4().to_string(),
+            start_time,
+            end_time: Some(end_time),
+            duration_ms: Some((end_time - start_time).num_milliseconds()),
+            memory_used_mb: None,
+            error: result.as_ref().err().map(ToString::to_string),
+            logs: vec![log],
+            cold_start: Some(cold_start),
+            init_duration_ms: None,
+        });
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sirsi_key_vault::secret::{InMemorySecretManager, SecretValue};
+    use sirsi_key_vault::Credential;
+
+    #[tokio::test]
+    async fn test_secrets_are_resolved_per_invocation() {
+        let secrets = Arc::new(InMemorySecretManager::new());
+        Credential::plaintext(r#"{"password":"first-pass"}"#).ingest(secrets.as_ref(), "db/prod").await.unwrap();
+        let environment = HashMap::from([
+            ("DB_PASSWORD".to_string(), "vault:db/prod#password".to_string()),
+            ("REGION".to_string(), "local".to_string()),
+        ]);
+        let runtime = LocalFunctionRuntime::new()
+            .with_env_handler("connect", |_, env: HashMap<String, String>| async move {
+                Ok(format!("{}@{}", env["DB_PASSWORD"], env["REGION"]).into_bytes())
+            })
+            .with_environment("connect", &environment)
+            .unwrap();
+        // Without a secret manager the reference can't be resolved
+        assert!(matches!(runtime.invoke("connect", Vec::new()).await, Err(ComputeError::Config(_))));
+
+        let runtime = runtime.with_secret_manager(secrets.clone());
+        assert_eq!(runtime.invoke("connect", b"{}".to_vec()).await.unwrap(), b"first-pass@local");
+        let mut rotated = secrets.get_secret("db/prod").await.unwrap();
+        rotated.value = SecretValue::Plain(r#"{"password":"second-pass"}"#.into());
+        secrets.update_secret(rotated).await.unwrap();
+        assert_eq!(runtime.invoke("connect", b"{}".to_vec()).await.unwrap(), b"second-pass@local");
+
+        let invocations = runtime.invocations("connect");
+        assert_eq!(invocations.len(), 3);
+        assert!(invocations.iter().all(|i| i.logs.iter().all(|l| !l.contains("-pass"))));
+        let cold: Vec<Option<bool>> = invocations.iter().map(|i| i.cold_start).collect();
+        assert_eq!(cold, vec![Some(true), Some(false), Some(false)]);
+    }
+}
@@ -0,0 +1,822 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+use super::schedule::CronSchedule;
+use crate::error::{ComputeError, ComputeResult};
+use crate::provider::Provider;
+
+pub const ENVELOPE_VERSION: &str = "1";
+
+// Attributes added to a message moved to a dead-letter queue
+pub const DEAD_LETTER_REASON_ATTRIBUTE: &str = "sirsi-dead-letter-reason";
+pub const DEAD_LETTER_SOURCE_ATTRIBUTE: &str = "sirsi-dead-letter-source";
+
+// The provider resource behind a queue, when there is one
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NativeQueue {
+    Sqs { arn: String },
+    // `projects/<project>/topics/<topic>`
+    PubSub { topic: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueueRef {
+    // The data-services queue id the poller reads from
+    pub queue_id: String,
+    #[serde(default)]
+    pub native: Option<NativeQueue>,
+}
+
+impl QueueRef {
+    pub fn new(queue_id: impl Into<String>) -> Self {
+        Self { queue_id: queue_id.into(), native: None }
+    }
+
+    pub fn with_native(mut self, native: NativeQueue) -> Self {
+        self.native = Some(native);
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EventSource {
+    Queue(QueueRef),
+    // `payload` is handed to every run as is
+    Schedule { cron: String, payload: Value },
+}
+
+// Where the function runs, which decides whether a provider can deliver to it directly
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FunctionTarget {
+    #[default]
+    Invoker,
+    Lambda { arn: String },
+    CloudRun { url: String, service_account: String },
+}
+
+// A failed batch is retried whole, `retry_delay_seconds` after the first failure and doubling
+// from there; a message that fails `max_retries` more times goes to the dead-letter queue, or
+// is dropped when there is none.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorPolicy {
+    pub max_retries: u32,
+    pub retry_delay_seconds: u32,
+    pub max_retry_delay_seconds: u32,
+    pub dead_letter_queue: Option<QueueRef>,
+}
+
+impl Default for ErrorPolicy {
+    fn default() -> Self {
+        Self { max_retries: 3, retry_delay_seconds: 10, max_retry_delay_seconds: 300, dead_letter_queue: None }
+    }
+}
+
+impl ErrorPolicy {
+    fn retry_delay(&self, attempt: u32) -> Duration {
+        let delay = (self.retry_delay_seconds as u64).saturating_mul(1 << attempt.saturating_sub(1).min(20));
+        Duration::from_secs(delay.min(self.max_retry_delay_seconds as u64))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MappingConfig {
+    pub function: String,
+    pub source: EventSource,
+    #[serde(default)]
+    pub target: FunctionTarget,
+    pub batch_size: u32,
+    // Batches in flight at once
+    pub max_concurrency: u32,
+    pub error_policy: ErrorPolicy,
+    pub enabled: bool,
+}
+
+impl MappingConfig {
+    pub fn queue(function: impl Into<String>, queue: QueueRef) -> Self {
+        Self {
+            function: function.into(),
+            source: EventSource::Queue(queue),
+            target: FunctionTarget::default(),
+            batch_size: 10,
+            max_concurrency: 2,
+            error_policy: ErrorPolicy::default(),
+            enabled: true,
+        }
+    }
+
+    pub fn schedule(function: impl Into<String>, cron: impl Into<String>, payload: Value) -> Self {
+        Self {
+            source: EventSource::Schedule { cron: cron.into(), payload },
+            max_concurrency: 1,
+            ..Self::queue(function, QueueRef::new(""))
+        }
+    }
+
+    pub fn with_batch_size(mut self, batch_size: u32) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    pub fn with_max_concurrency(mut self, max_concurrency: u32) -> Self {
+        self.max_concurrency = max_concurrency;
+        self
+    }
+
+    pub fn with_error_policy(mut self, error_policy: ErrorPolicy) -> Self {
+        self.error_policy = error_policy;
+        self
+    }
+
+    pub fn with_target(mut self, target: FunctionTarget) -> Self {
+        self.target = target;
+        self
+    }
+
+    pub fn disabled(mut self) -> Self {
+        self.enabled = false;
+        self
+    }
+
+    fn validate(&self) -> ComputeResult<()> {
+        if self.function.trim().is_empty() {
+            return Err(ComputeError::Validation("A mapping needs a function".to_string()));
+        }
+        if !(1..=1000).contains(&self.batch_size) {
+            return Err(ComputeError::Validation(format!("Batch size {} is outside 1-1000", self.batch_size)));
+        }
+        if !(1..=100).contains(&self.max_concurrency) {
+            return Err(ComputeError::Validation(format!("Max concurrency {} is outside 1-100", self.max_concurrency)));
+        }
+        if self.error_policy.retry_delay_seconds > self.error_policy.max_retry_delay_seconds {
+            return Err(ComputeError::Validation("Retry delay is longer than the maximum retry delay".to_string()));
+        }
+        match &self.source {
+            EventSource::Queue(queue) if queue.queue_id.trim().is_empty() => {
+                Err(ComputeError::Validation("A queue mapping needs a queue id".to_string()))
+            }
+            EventSource::Queue(_) => Ok(()),
+            EventSource::Schedule { cron, .. } => CronSchedule::parse(cron).map(|_| ()),
+        }
+    }
+}
+
+// SQS moves messages to a dead-letter queue itself, after `max_receive_count` receives
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SqsRedrive {
+    pub dead_letter_arn: String,
+    pub max_receive_count: u32,
+}
+
+// A mapping the provider runs instead of our poller. Functions behind one receive the
+// provider's own event format rather than `EventEnvelope`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NativeMapping {
+    LambdaEventSource {
+        function_arn: String,
+        event_source_arn: String,
+        batch_size: u32,
+        maximum_concurrency: u32,
+        // Set as the source queue's redrive policy
+        redrive: Option<SqsRedrive>,
+    },
+    PubSubPush {
+        topic: String,
+        subscription: String,
+        push_endpoint: String,
+        service_account: String,
+        max_delivery_attempts: u32,
+        minimum_backoff_seconds: u32,
+        maximum_backoff_seconds: u32,
+        dead_letter_topic: Option<String>,
+    },
+}
+
+// Translates a mapping to one its provider can run, or `None` when only the poller can: the
+// queue and function are on different providers, a dead-letter queue has no native
+// counterpart, the source is a schedule, or a Pub/Sub push would need batches.
+pub fn native_mapping(id: &str, config: &MappingConfig) -> Option<NativeMapping> {
+    let EventSource::Queue(queue) = &config.source else {
+        return None;
+    };
+    let policy = &config.error_policy;
+    let dead_letter = policy.dead_letter_queue.as_ref().map(|q| q.native.as_ref());
+    match (queue.native.as_ref()?, &config.target) {
+        (NativeQueue::Sqs { arn }, FunctionTarget::Lambda { arn: function_arn }) => {
+            let redrive = match dead_letter {
+                None => None,
+                Some(Some(NativeQueue::Sqs { arn })) => {
+                    Some(SqsRedrive { dead_letter_arn: arn.clone(), max_receive_count: policy.max_retries + 1 })
+                }
+                Some(_) => return None,
+            };
+            Some(NativeMapping::LambdaEventSource {
+                function_arn: function_arn.clone(),
+                event_source_arn: arn.clone(),
+                batch_size: config.batch_size,
+                // Lambda's floor for SQS sources
+                maximum_concurrency: config.max_concurrency.clamp(2, 1000),
+                redrive,
+            })
+        }
+        // Push delivers one message per request
+        (NativeQueue::PubSub { topic }, FunctionTarget::CloudRun { url, service_account }) if config.batch_size == 1 => {
+            let dead_letter_topic = match dead_letter {
+                None => None,
+                Some(Some(NativeQueue::PubSub { topic })) => Some(topic.clone()),
+                Some(_) => return None,
+            };
+            let project = topic.strip_prefix("projects/")?.split('/').next()?;
+            Some(NativeMapping::PubSubPush {
+                topic: topic.clone(),
+                subscription: format!("projects/{}/subscriptions/sirsi-mapping-{}", project, id),
+                push_endpoint: url.clone(),
+                service_account: service_account.clone(),
+                max_delivery_attempts: (policy.max_retries + 1).clamp(5, 100),
+                minimum_backoff_seconds: policy.retry_delay_seconds.min(600),
+                maximum_backoff_seconds: policy.max_retry_delay_seconds.min(600),
+                dead_letter_topic,
+            })
+        }
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MappingHandler {
+    Poller,
+    Native { id: String, mapping: NativeMapping },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventSourceMapping {
+    pub id: String,
+    pub config: MappingConfig,
+    pub handler: MappingHandler,
+    pub invocations: u64,
+    pub failures: u64,
+    pub dead_lettered: u64,
+    pub last_error: Option<String>,
+    pub last_error_at: Option<DateTime<Utc>>,
+    pub last_invoked_at: Option<DateTime<Utc>>,
+    // Schedules only
+    pub next_run_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+// What a function triggered through the poller receives, as JSON:
+// `{"version": "1", "mapping_id": "...", "function": "...", "records": [...]}`.
+// A message record carries `body` when the message is UTF-8 text and `body_base64` otherwise;
+// `attempt` is 1 on first delivery. A schedule record carries the mapping's payload and the
+// time the run was due.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventEnvelope {
+    pub version: String,
+    pub mapping_id: String,
+    pub function: String,
+    pub records: Vec<EventRecord>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EventRecord {
+    Message {
+        message_id: String,
+        queue_id: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        body: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        body_base64: Option<String>,
+        attributes: BTreeMap<String, String>,
+        published_at: DateTime<Utc>,
+        attempt: u32,
+    },
+    Schedule {
+        scheduled_time: DateTime<Utc>,
+        payload: Value,
+    },
+}
+
+impl EventRecord {
+    fn message(queue_id: &str, message: &QueuedMessage) -> Self {
+        let (body, body_base64) = match std::str::from_utf8(&message.data) {
+            Ok(text) => (Some(text.to_string()), None),
+            Err(_) => (None, Some(base64::engine::general_purpose::STANDARD.encode(&message.data))),
+        };
+        EventRecord::Message {
+            message_id: message.id.clone(),
+            queue_id: queue_id.to_string(),
+            body,
+            body_base64,
+            attributes: message.attributes.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+            published_at: message.published_at,
+            attempt: message.delivery_count,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueuedMessage {
+    pub id: String,
+    pub data: Vec<u8>,
+    pub attributes: HashMap<String, String>,
+    pub published_at: DateTime<Utc>,
+    // 1 on first delivery
+    pub delivery_count: u32,
+}
+
+// The queue operations the poller needs, implemented over data-services' `MessageOperations`.
+// Received messages stay invisible to other consumers until acknowledged or released.
+#[async_trait]
+pub trait EventQueue: Send + Sync {
+    async fn receive(&self, queue_id: &str, max_messages: usize) -> ComputeResult<Vec<QueuedMessage>>;
+    async fn ack(&self, queue_id: &str, message_id: &str) -> ComputeResult<()>;
+    // Makes a received message visible again after `delay`
+    async fn retry_after(&self, queue_id: &str, message_id: &str, delay: Duration) -> ComputeResult<()>;
+    async fn send(&self, queue_id: &str, data: Vec<u8>, attributes: HashMap<String, String>) -> ComputeResult<()>;
+}
+
+#[async_trait]
+pub trait FunctionInvoker: Send + Sync {
+    async fn invoke(&self, function: &str, payload: Vec<u8>) -> ComputeResult<Vec<u8>>;
+}
+
+#[async_trait]
+impl FunctionInvoker for Box<dyn Provider> {
+    async fn invoke(&self, function: &str, payload: Vec<u8>) -> ComputeResult<Vec<u8>> {
+        self.invoke_function(function, payload).await
+    }
+}
+
+// Creates and manages native mappings on the provider they belong to
+#[async_trait]
+pub trait NativeMappings: Send + Sync {
+    async fn create(&self, mapping: &NativeMapping, enabled: bool) -> ComputeResult<String>;
+    async fn set_enabled(&self, id: &str, mapping: &NativeMapping, enabled: bool) -> ComputeResult<()>;
+    async fn delete(&self, id: &str, mapping: &NativeMapping) -> ComputeResult<()>;
+    // What the provider last reported going wrong, if anything
+    async fn last_error(&self, id: &str, mapping: &NativeMapping) -> ComputeResult<Option<String>>;
+}
+
+#[async_trait]
+pub trait MappingStore: Send + Sync {
+    async fn save_mapping(&self, mapping: &EventSourceMapping) -> ComputeResult<()>;
+    async fn get_mapping(&self, id: &str) -> ComputeResult<Option<EventSourceMapping>>;
+    async fn list_mappings(&self) -> ComputeResult<Vec<EventSourceMapping>>;
+    async fn delete_mapping(&self, id: &str) -> ComputeResult<()>;
+}
+
+#[derive(Default)]
+pub struct InMemoryMappingStore {
+    mappings: RwLock<HashMap<String, EventSourceMapping>>,
+}
+
+impl InMemoryMappingStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl MappingStore for InMemoryMappingStore {
+    async fn save_mapping(&self, mapping: &EventSourceMapping) -> ComputeResult<()> {
+        self.mappings.write().await.insert(mapping.id.clone(), mapping.clone());
+        Ok(())
+    }
+
+    async fn get_mapping(&self, id: &str) -> ComputeResult<Option<EventSourceMapping>> {
+        Ok(self.mappings.read().await.get(id).cloned())
+    }
+
+    async fn list_mappings(&self) -> ComputeResult<Vec<EventSourceMapping>> {
+        let mut mappings: Vec<EventSourceMapping> = self.mappings.read().await.values().cloned().collect();
+        mappings.sort_by_key(|m| m.created_at);
+        Ok(mappings)
+    }
+
+    async fn delete_mapping(&self, id: &str) -> ComputeResult<()> {
+        self.mappings.write().await.remove(id);
+        Ok(())
+    }
+}
+
+// What one poll of a mapping did, folded into its counters afterwards
+#[derive(Debug, Default)]
+struct PollOutcome {
+    invocations: u64,
+    failures: u64,
+    dead_lettered: u64,
+    last_error: Option<String>,
+    last_invoked_at: Option<DateTime<Utc>>,
+    next_run_at: Option<DateTime<Utc>>,
+}
+
+pub struct EventSourceMappings {
+    queue: Arc<dyn EventQueue>,
+    invoker: Arc<dyn FunctionInvoker>,
+    store: Arc<dyn MappingStore>,
+    native: Option<Arc<dyn NativeMappings>>,
+}
+
+impl EventSourceMappings {
+    pub fn new(queue: Arc<dyn EventQueue>, invoker: Arc<dyn FunctionInvoker>) -> Self {
+        Self { queue, invoker, store: Arc::new(InMemoryMappingStore::new()), native: None }
+    }
+
+    pub fn with_store(mut self, store: Arc<dyn MappingStore>) -> Self {
+        self.store = store;
+        self
+    }
+
+    pub fn with_native(mut self, native: Arc<dyn NativeMappings>) -> Self {
+        self.native = Some(native);
+        self
+    }
+
+    // Hands the mapping to its provider when it can run it, and to the poller otherwise
+    pub async fn create_mapping(&self, config: MappingConfig) -> ComputeResult<EventSourceMapping> {
+        config.validate()?;
+        let id = uuid::Uuid::new_v4().to_string();
+        let handler = match (&self.native, native_mapping(&id, &config)) {
+            (Some(native), Some(mapping)) => {
+                let native_id = native.create(&mapping, config.enabled).await?;
+                info!("Mapping {} for {} runs natively as {}", id, config.function, native_id);
+                MappingHandler::Native { id: native_id, mapping }
+            }
+            _ => MappingHandler::Poller,
+        };
+        let now = Utc::now();
+        let next_run_at = match &config.source {
+            EventSource::Schedule { cron, .. } => CronSchedule::parse(cron)?.next_after(now),
+            EventSource::Queue(_) => None,
+        };
+        let mapping = EventSourceMapping {
+            id,
+            config,
+            handler,
+            invocations: 0,
+            failures: 0,
+            dead_lettered: 0,
+            last_error: None,
+            last_error_at: None,
+            last_invoked_at: None,
+            next_run_at,
+            created_at: now,
+        };
+        self.store.save_mapping(&mapping).await?;
+        Ok(mapping)
+    }
+
+    // Native mappings report the provider's last error
+    pub async fn get_mapping(&self, id: &str) -> ComputeResult<EventSourceMapping> {
+        let mut mapping = self.load(id).await?;
+        if let (Some(native), MappingHandler::Native { id, mapping: native_mapping }) = (&self.native, &mapping.handler) {
+            if let Some(error) = native.last_error(id, native_mapping).await? {
+                mapping.last_error = Some(error);
+            }
+        }
+        Ok(mapping)
+    }
+
+    pub async fn list_mappings(&self, function: Option<&str>) -> ComputeResult<Vec<EventSourceMapping>> {
+        let mappings = self.store.list_mappings().await?;
+        Ok(mappings.into_iter().filter(|m| function.is_none_or(|f| m.config.function == f)).collect())
+    }
+
+    pub async fn set_enabled(&self, id: &str, enabled: bool) -> ComputeResult<EventSourceMapping> {
+        let mut mapping = self.load(id).await?;
+        if let MappingHandler::Native { id, mapping: native_mapping } = &mapping.handler {
+            self.native_handler()?.set_enabled(id, native_mapping, enabled).await?;
+        }
+        if enabled && !mapping.config.enabled {
+            // A re-enabled schedule resumes from now rather than replaying what it missed
+            if let EventSource::Schedule { cron, .. } = &mapping.config.source {
+                mapping.next_run_at = CronSchedule::parse(cron)?.next_after(Utc::now());
+            }
+        }
+        mapping.config.enabled = enabled;
+        self.store.save_mapping(&mapping).await?;
+        Ok(mapping)
+    }
+
+    pub async fn delete_mapping(&self, id: &str) -> ComputeResult<()> {
+        let mapping = self.load(id).await?;
+        if let MappingHandler::Native { id, mapping: native_mapping } = &mapping.handler {
+            self.native_handler()?.delete(id, native_mapping).await?;
+        }
+        self.store.delete_mapping(&mapping.id).await
+    }
+
+    // One round over every enabled poller mapping; returns how many invocations were made
+    pub async fn poll(&self, now: DateTime<Utc>) -> ComputeResult<u64> {
+        let mappings: Vec<EventSourceMapping> = self
+            .store
+            .list_mappings()
+            .await?
+            .into_iter()
+            .filter(|m| m.config.enabled && m.handler == MappingHandler::Poller)
+            .collect();
+        let polls = mappings.iter().map(|mapping| async move {
+            let outcome = match &mapping.config.source {
+                EventSource::Queue(queue) => self.poll_queue(mapping, queue).await,
+                EventSource::Schedule { cron, payload } => self.run_schedule(mapping, cron, payload, now).await,
+            };
+            (mapping, outcome)
+        });
+        let mut invocations = 0;
+        for (mapping, outcome) in futures::future::join_all(polls).await {
+            let outcome = outcome.unwrap_or_else(|e| {
+                warn!("Polling mapping {} failed: {}", mapping.id, e);
+                PollOutcome { last_error: Some(e.to_string()), ..PollOutcome::default() }
+            });
+            invocations += outcome.invocations;
+            self.record(&mapping.id, outcome, now).await?;
+        }
+        Ok(invocations)
+    }
+
+    pub fn spawn_poller(self: Arc<Self>, interval: Duration) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = self.poll(Utc::now()).await {
+                    warn!("Event source polling failed: {}", e);
+                }
+            }
+        })
+    }
+
+    async fn load(&self, id: &str) -> ComputeResult<EventSourceMapping> {
+        self.store
+            .get_mapping(id)
+            .await?
+            .ok_or_else(|| ComputeError::NotFound(format!("Event source mapping {} not found", id)))
+    }
+
+    fn native_handler(&self) -> ComputeResult<&Arc<dyn NativeMappings>> {
+        self.native
+            .as_ref()
+            .ok_or_else(|| ComputeError::Config("Mapping is native but no native mapping handler is configured".to_string()))
+    }
+
+    // Re-read so a concurrent enable, disable or delete is not overwritten
+    async fn record(&self, id: &str, outcome: PollOutcome, now: DateTime<Utc>) -> ComputeResult<()> {
+        let Some(mut mapping) = self.store.get_mapping(id).await? else {
+            return Ok(());
+        };
+        mapping.invocations += outcome.invocations;
+        mapping.failures += outcome.failures;
+        mapping.dead_lettered += outcome.dead_lettered;
+        mapping.last_invoked_at = outcome.last_invoked_at.or(mapping.last_invoked_at);
+        mapping.next_run_at = outcome.next_run_at.or(mapping.next_run_at);
+        if let Some(error) = outcome.last_error {
+            mapping.last_error = Some(error);
+            mapping.last_error_at = Some(now);
+        }
+        self.store.save_mapping(&mapping).await
+    }
+
+    fn envelope(mapping: &EventSourceMapping, records: Vec<EventRecord>) -> ComputeResult<Vec<u8>> {
+        let envelope = EventEnvelope {
+            version: ENVELOPE_VERSION.to_string(),
+            mapping_id: mapping.id.clone(),
+            function: mapping.config.function.clone(),
+            records,
+        };
+        serde_json::to_vec(&envelope).map_err(|e| ComputeError::Internal(format!("Failed to encode event envelope: {}", e)))
+    }
+
+    // Up to `max_concurrency` batches, invoked side by side
+    async fn poll_queue(&self, mapping: &EventSourceMapping, queue: &QueueRef) -> ComputeResult<PollOutcome> {
+        let batch_size = mapping.config.batch_size as usize;
+        let mut batches = Vec::new();
+        for _ in 0..mapping.config.max_concurrency {
+            let batch = self.queue.receive(&queue.queue_id, batch_size).await?;
+            let full = batch.len() == batch_size;
+            if !batch.is_empty() {
+                batches.push(batch);
+            }
+            if !full {
+                break;
+            }
+        }
+        let deliveries = batches.iter().map(|batch| self.deliver(mapping, &queue.queue_id, batch));
+        let mut outcome = PollOutcome::default();
+        for delivery in futures::future::join_all(deliveries).await {
+            let (failed, dead_lettered) = delivery?;
+            outcome.invocations += 1;
+            outcome.dead_lettered += dead_lettered;
+            outcome.last_invoked_at = Some(Utc::now());
+            if let Some(error) = failed {
+                outcome.failures += 1;
+                outcome.last_error = Some(error);
+            }
+        }
+        Ok(outcome)
+    }
+
+    // The invocation error, if any, and how many messages were dead-lettered
+    async fn deliver(
+        &self,
+        mapping: &EventSourceMapping,
+        queue_id: &str,
+        batch: &[QueuedMessage],
+    ) -> ComputeResult<(Option<String>, u64)> {
+        let records = batch.iter().map(|m| EventRecord::message(queue_id, m)).collect();
+        let payload = Self::envelope(mapping, records)?;
+        let error = match self.invoker.invoke(&mapping.config.function, payload).await {
+            Ok(_) => {
+                for message in batch {
+                    self.queue.ack(queue_id, &message.id).await?;
+                }
+                return Ok((None, 0));
+            }
+            Err(e) => e.to_string(),
+        };
+        warn!("Mapping {}: {} failed on a batch of {}: {}", mapping.id, mapping.config.function, batch.len(), error);
+        let policy = &mapping.config.error_policy;
+        let mut dead_lettered = 0;
+        for message in batch {
+            if message.delivery_count <= policy.max_retries {
+                self.queue.retry_after(queue_id, &message.id, policy.retry_delay(message.delivery_count)).await?;
+                continue;
+            }
+            match &policy.dead_letter_queue {
+                Some(dlq) => {
+                    let mut attributes = message.attributes.clone();
+                    attributes.insert(DEAD_LETTER_REASON_ATTRIBUTE.to_string(), error.clone());
+                    attributes.insert(DEAD_LETTER_SOURCE_ATTRIBUTE.to_string(), queue_id.to_string());
+                    self.queue.send(&dlq.queue_id, message.data.clone(), attributes).await?;
+                    dead_lettered += 1;
+                }
+                None => warn!("Dropping message {} from {} after {} attempts", message.id, queue_id, message.delivery_count),
+            }
+            self.queue.ack(queue_id, &message.id).await?;
+        }
+        Ok((Some(error), dead_lettered))
+    }
+
+    // Runs once when due, however many runs were missed; retries straight away since the
+    // next run is usually closer than any backoff
+    async fn run_schedule(
+        &self,
+        mapping: &EventSourceMapping,
+        cron: &str,
+        payload: &Value,
+        now: DateTime<Utc>,
+    ) -> ComputeResult<PollOutcome> {
+        let schedule = CronSchedule::parse(cron)?;
+        let mut outcome = PollOutcome::default();
+        let Some(due) = mapping.next_run_at.filter(|due| *due <= now) else {
+            outcome.next_run_at = mapping.next_run_at.or_else(|| schedule.next_after(now));
+            return Ok(outcome);
+        };
+        outcome.next_run_at = schedule.next_after(now);
+        let records = vec![EventRecord::Schedule { scheduled_time: due, payload: payload.clone() }];
+        let envelope = Self::envelope(mapping, records)?;
+        let policy = &mapping.config.error_policy;
+        for _ in 0..=policy.max_retries {
+            outcome.invocations += 1;
+            outcome.last_invoked_at = Some(Utc::now());
+            match self.invoker.invoke(&mapping.config.function, envelope.clone()).await {
+                Ok(_) => return Ok(outcome),
+                Err(e) => {
+                    outcome.failures += 1;
+                    outcome.last_error = Some(e.to_string());
+                }
+            }
+        }
+        let error = outcome.last_error.clone().unwrap_or_default();
+        warn!("Mapping {}: scheduled run of {} due {} failed: {}", mapping.id, mapping.config.function, due, error);
+        if let Some(dlq) = &policy.dead_letter_queue {
+            let attributes = HashMap::from([(DEAD_LETTER_REASON_ATTRIBUTE.to_string(), error)]);
+            self.queue.send(&dlq.queue_id, envelope, attributes).await?;
+            outcome.dead_lettered += 1;
+        }
+        Ok(outcome)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serverless::LocalFunctionRuntime;
+    use chrono::TimeZone;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct RecordingQueue {
+        sent: Mutex<Vec<(String, Vec<u8>)>>,
+    }
+
+    #[async_trait]
+    impl EventQueue for RecordingQueue {
+        async fn receive(&self, _queue_id: &str, _max_messages: usize) -> ComputeResult<Vec<QueuedMessage>> {
+            Ok(Vec::new())
+        }
+
+        async fn ack(&self, _queue_id: &str, _message_id: &str) -> ComputeResult<()> {
+            Ok(())
+        }
+
+        async fn retry_after(&self, _queue_id: &str, _message_id: &str, _delay: Duration) -> ComputeResult<()> {
+            Ok(())
+        }
+
+        async fn send(&self, queue_id: &str, data: Vec<u8>, _attributes: HashMap<String, String>) -> ComputeResult<()> {
+            self.sent.lock().unwrap().push((queue_id.to_string(), data));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_native_translation() {
+        let sqs = |name: &str| NativeQueue::Sqs { arn: format!("arn:aws:sqs:us-east-1:123456789012:{}", name) };
+        let lambda = FunctionTarget::Lambda { arn: "arn:aws:lambda:us-east-1:123456789012:function:resize".into() };
+        let policy = |dlq: QueueRef| ErrorPolicy { max_retries: 4, dead_letter_queue: Some(dlq), ..ErrorPolicy::default() };
+
+        let config = MappingConfig::queue("resize", QueueRef::new("uploads").with_native(sqs("uploads")))
+            .with_target(lambda.clone())
+            .with_max_concurrency(1)
+            .with_error_policy(policy(QueueRef::new("uploads-dlq").with_native(sqs("uploads-dlq"))));
+        match native_mapping("m-1", &config).unwrap() {
+            NativeMapping::LambdaEventSource { event_source_arn, batch_size, maximum_concurrency, redrive, .. } => {
+                assert!(event_source_arn.ends_with(":uploads"));
+                assert_eq!((batch_size, maximum_concurrency), (10, 2));
+                assert_eq!(redrive.unwrap().max_receive_count, 5);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        // A dead-letter queue SQS can't redrive to leaves it to the poller, as does a local function
+        let local_dlq = config.clone().with_error_policy(policy(QueueRef::new("uploads-dlq")));
+        assert_eq!(native_mapping("m-1", &local_dlq), None);
+        assert_eq!(native_mapping("m-1", &config.clone().with_target(FunctionTarget::Invoker)), None);
+
+        let pubsub = NativeQueue::PubSub { topic: "projects/acme/topics/orders".into() };
+        let cloud_run = FunctionTarget::CloudRun { url: "https://orders-xyz.a.run.app".into(), service_account: "push@acme.iam".into() };
+        let config = MappingConfig::queue("orders", QueueRef::new("orders").with_native(pubsub)).with_target(cloud_run);
+        assert_eq!(native_mapping("m-2", &config), None);
+        match native_mapping("m-2", &config.with_batch_size(1)).unwrap() {
+            NativeMapping::PubSubPush { subscription, max_delivery_attempts, dead_letter_topic, .. } => {
+                assert_eq!(subscription, "projects/acme/subscriptions/sirsi-mapping-m-2");
+                assert_eq!(max_delivery_attempts, 5);
+                assert_eq!(dead_letter_topic, None);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_schedule_runs_once_when_due_and_dead_letters_failures() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let seen = calls.clone();
+        let runtime = LocalFunctionRuntime::new().with_handler("report", move |payload: Vec<u8>| {
+            seen.lock().unwrap().push(serde_json::from_slice::<EventEnvelope>(&payload).unwrap());
+            async { Err(ComputeError::Provider("report backend down".into())) }
+        });
+        let queue = Arc::new(RecordingQueue::default());
+        let mappings = EventSourceMappings::new(queue.clone(), Arc::new(runtime));
+        let policy = ErrorPolicy { max_retries: 1, dead_letter_queue: Some(QueueRef::new("report-dlq")), ..ErrorPolicy::default() };
+        let config = MappingConfig::schedule("report", "0 * * * *", serde_json::json!({"kind": "hourly"})).with_error_policy(policy);
+        let mapping = mappings.create_mapping(config).await.unwrap();
+        assert_eq!(mapping.handler, MappingHandler::Poller);
+
+        let mut stored = mappings.get_mapping(&mapping.id).await.unwrap();
+        let due = Utc.with_ymd_and_hms(2025, 7, 9, 10, 0, 0).unwrap();
+        stored.next_run_at = Some(due);
+        mappings.store.save_mapping(&stored).await.unwrap();
+
+        // Two hours late still runs once
+        let now = Utc.with_ymd_and_hms(2025, 7, 9, 12, 5, 0).unwrap();
+        assert_eq!(mappings.poll(now).await.unwrap(), 2);
+        assert_eq!(mappings.poll(now).await.unwrap(), 0);
+        let envelope = calls.lock().unwrap()[0].clone();
+        assert_eq!(envelope.version, ENVELOPE_VERSION);
+        assert_eq!(envelope.records, vec![EventRecord::Schedule { scheduled_time: due, payload: serde_json::json!({"kind": "hourly"}) }]);
+
+        let after = mappings.get_mapping(&mapping.id).await.unwrap();
+        assert_eq!((after.invocations, after.failures, after.dead_lettered), (2, 2, 1));
+        assert_eq!(after.next_run_at, Some(Utc.with_ymd_and_hms(2025, 7, 9, 13, 0, 0).unwrap()));
+        assert!(after.last_error.unwrap().contains("report backend down"));
+        let (dlq, sent) = queue.sent.lock().unwrap()[0].clone();
+        assert_eq!(dlq, "report-dlq");
+        assert_eq!(serde_json::from_slice::<EventEnvelope>(&sent).unwrap(), envelope);
+
+        let disabled = mappings.set_enabled(&mapping.id, false).await.unwrap();
+        assert!(!disabled.config.enabled);
+    }
+}
@@ -1,6 +1,26 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+use crate::error::ComputeResult;
+
+pub mod code;
+pub mod concurrency;
+pub mod local;
+pub mod mapping;
+pub mod schedule;
+pub mod secrets;
+
+pub use code::{CodeRef, CodeStore, FileCodeStore};
+pub use concurrency::{recommend_provisioned_concurrency, ConcurrencyRecommendation, InvocationLog, NativeConcurrency};
+pub use local::LocalFunctionRuntime;
+pub use mapping::{
+    native_mapping, ErrorPolicy, EventEnvelope, EventQueue, EventRecord, EventSource, EventSourceMapping, EventSourceMappings,
+    FunctionInvoker, FunctionTarget, InMemoryMappingStore, MappingConfig, MappingHandler, MappingStore, NativeMapping,
+    NativeMappings, NativeQueue, QueueRef, QueuedMessage,
+};
+pub use schedule::CronSchedule;
+pub use secrets::{NativeSecretStore, SecretEnvironment};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Function {
     pub name: String,
@@ -22,14 +42,23 @@ pub struct FunctionConfig {
     pub name: String,
     pub runtime: String,
     pub handler: String,
+    // Inline package for small functions; large ones are uploaded first and passed as `code_ref`
+    #[serde(default)]
     pub code: Vec<u8>,
+    #[serde(default)]
+    pub code_ref: Option<CodeRef>,
     pub description: Option<String>,
     pub memory_mb: i32,
     pub timeout_sec: i32,
+    // Values may be `vault:<secret id>#<field>` references; providers deploy them through their
+    // own secret stores (see `secrets`) and never as plaintext
     pub environment: HashMap<String, String>,
     pub labels: HashMap<String, String>,
     pub annotations: HashMap<String, String>,
     pub vpc_config: Option<VpcConfig>,
+    // Executions kept initialised ahead of traffic; see `concurrency::native_concurrency`
+    #[serde(default)]
+    pub provisioned_concurrency: Option<i32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -38,7 +67,7 @@ pub struct VpcConfig {
     pub security_group_ids: Vec<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum FunctionState {
     Pending,
@@ -55,6 +84,11 @@ pub struct FunctionMetrics {
     pub duration_ms: i64,
     pub concurrent_executions: i32,
     pub memory_utilization: f64,
+    // Share of invocations that landed on a fresh instance
+    #[serde(default)]
+    pub cold_start_rate: f64,
+    #[serde(default)]
+    pub p95_init_duration_ms: Option<i64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -67,6 +101,11 @@ pub struct FunctionInvocation {
     pub memory_used_mb: Option<i32>,
     pub error: Option<String>,
     pub logs: Vec<String>,
+    // `None` when the provider gave no signal either way
+    #[serde(default)]
+    pub cold_start: Option<bool>,
+    #[serde(default)]
+    pub init_duration_ms: Option<i64>,
 }
 
 impl Function {
@@ -120,6 +159,7 @@ impl FunctionConfig {
             runtime,
             handler,
             code,
+            code_ref: None,
             description: None,
             memory_mb: 128,
             timeout_sec: 30,
@@ -127,6 +167,7 @@ impl FunctionConfig {
             labels: HashMap::new(),
             annotations: HashMap::new(),
             vpc_config: None,
+            provisioned_concurrency: None,
         }
     }
 
@@ -154,6 +195,24 @@ impl FunctionConfig {
         self.vpc_config = Some(vpc_config);
         self
     }
+
+    pub fn with_provisioned_concurrency(mut self, executions: i32) -> Self {
+        self.provisioned_concurrency = Some(executions);
+        self
+    }
+
+    pub fn with_code_ref(mut self, code_ref: CodeRef) -> Self {
+        self.code_ref = Some(code_ref);
+        self
+    }
+
+    // Package bytes for providers that take them inline; an uploaded `code_ref` wins
+    pub async fn resolve_code(&self) -> ComputeResult<Vec<u8>> {
+        match &self.code_ref {
+            Some(code_ref) => code_ref.load().await,
+            None => Ok(self.code.clone()),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -213,4 +272,33 @@ mod tests {
         assert_eq!(config.code, code);
         assert!(config.vpc_config.is_none());
     }
+
+    #[tokio::test]
+    async fn test_resolve_code_inline_and_uploaded() {
+        let inline = include_bytes!("../tests/test_function.js").to_vec();
+        // Configs serialized before code_ref existed still deserialize and deploy inline
+        let mut json = serde_json::to_value(FunctionConfig::new(
+            "small".to_string(),
+            "nodejs18.x".to_string(),
+            "index.handler".to_string(),
+            inline.clone(),
+        ))
+        .unwrap();
+        json.as_object_mut().unwrap().remove("code_ref");
+        let config: FunctionConfig = serde_json::from_value(json).unwrap();
+        assert_eq!(config.resolve_code().await.unwrap(), inline);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("package.zip");
+        let package = vec![7u8; 3 * 1024 * 1024];
+        std::fs::write(&path, &package).unwrap();
+        let code_ref = CodeRef {
+            digest: code::sha256_digest(&package),
+            location: format!("file://{}", path.display()),
+            size_bytes: package.len() as u64,
+        };
+        let config = FunctionConfig::new("large".to_string(), "python3.12".to_string(), "app.handler".to_string(), Vec::new())
+            .with_code_ref(code_ref);
+        assert_eq!(config.resolve_code().await.unwrap(), package);
+    }
 }
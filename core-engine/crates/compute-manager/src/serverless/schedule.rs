@@ -0,0 +1,133 @@
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Timelike, Utc};
+
+use crate::error::{ComputeError, ComputeResult};
+
+// Five-field cron (`minute hour day-of-month month day-of-week`), evaluated in UTC. Fields take
+// `*`, numbers, `a-b` ranges, `/step` and comma lists; day-of-week 0 and 7 are both Sunday.
+// As in Vixie cron, when both day fields are restricted a day matching either one fires.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let mut bits = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().map_err(|_| format!("bad step in {:?}", part))?),
+            None => (part, 1),
+        };
+        if step == 0 {
+            return Err(format!("zero step in {:?}", part));
+        }
+        let number = |s: &str| s.parse::<u32>().map_err(|_| format!("bad value {:?}", s));
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((start, end)) => (number(start)?, number(end)?),
+                // `5/15` runs from 5 to the end of the range
+                None if part.contains('/') => (number(range)?, max),
+                None => (number(range)?, number(range)?),
+            },
+        };
+        if start < min || end > max || start > end {
+            return Err(format!("{:?} is outside {}-{}", part, min, max));
+        }
+        for value in (start..=end).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+impl CronSchedule {
+    pub fn parse(expression: &str) -> ComputeResult<Self> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let invalid = |reason: String| ComputeError::Validation(format!("Invalid cron expression {:?}: {}", expression, reason));
+        if fields.len() != 5 {
+            return Err(invalid("expected five fields".to_string()));
+        }
+        let mut weekdays = parse_field(fields[4], 0, 7).map_err(invalid)?;
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+        Ok(Self {
+            minutes: parse_field(fields[0], 0, 59).map_err(invalid)?,
+            hours: parse_field(fields[1], 0, 23).map_err(invalid)?,
+            days: parse_field(fields[2], 1, 31).map_err(invalid)?,
+            months: parse_field(fields[3], 1, 12).map_err(invalid)?,
+            weekdays,
+            days_restricted: fields[2] != "*",
+            weekdays_restricted: fields[4] != "*",
+        })
+    }
+
+    fn day_matches(&self, at: DateTime<Utc>) -> bool {
+        let day = self.days & (1 << at.day()) != 0;
+        let weekday = self.weekdays & (1 << at.weekday().num_days_from_sunday()) != 0;
+        match (self.days_restricted, self.weekdays_restricted) {
+            (true, true) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+
+    // The first minute strictly after `after` that the schedule fires on; `None` for
+    // schedules that can never fire, such as February 30th
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let midnight = |date: NaiveDate| date.and_hms_opt(0, 0, 0).map(|t| t.and_utc());
+        let mut at = after.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        // Enough for any schedule that fires at least once every few years
+        for _ in 0..100_000 {
+            if self.months & (1 << at.month()) == 0 {
+                let (year, month) = if at.month() == 12 { (at.year() + 1, 1) } else { (at.year(), at.month() + 1) };
+                at = midnight(NaiveDate::from_ymd_opt(year, month, 1)?)?;
+            } else if !self.day_matches(at) {
+                at = midnight(at.date_naive().succ_opt()?)?;
+            } else if self.hours & (1 << at.hour()) == 0 {
+                at = at.with_minute(0)? + Duration::hours(1);
+            } else if self.minutes & (1 << at.minute()) == 0 {
+                at += Duration::minutes(1);
+            } else {
+                return Some(at);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_cron_next_after() {
+        let at = |y, mo, d, h, mi| Utc.with_ymd_and_hms(y, mo, d, h, mi, 0).unwrap();
+        let every_quarter = CronSchedule::parse("*/15 * * * *").unwrap();
+        assert_eq!(every_quarter.next_after(at(2025, 7, 9, 10, 7)), Some(at(2025, 7, 9, 10, 15)));
+        assert_eq!(every_quarter.next_after(at(2025, 7, 9, 10, 45)), Some(at(2025, 7, 9, 11, 0)));
+
+        // Weekdays at 09:30; 2025-07-12 is a Saturday
+        let weekdays = CronSchedule::parse("30 9 * * 1-5").unwrap();
+        assert_eq!(weekdays.next_after(at(2025, 7, 11, 9, 30)), Some(at(2025, 7, 14, 9, 30)));
+
+        // Either day field matches when both are given; 7 is Sunday
+        let either = CronSchedule::parse("0 0 1 * 7").unwrap();
+        assert_eq!(either.next_after(at(2025, 7, 9, 0, 0)), Some(at(2025, 7, 13, 0, 0)));
+        assert_eq!(either.next_after(at(2025, 7, 27, 0, 0)), Some(at(2025, 8, 1, 0, 0)));
+
+        let year_end = CronSchedule::parse("59 23 31 12 *").unwrap();
+        assert_eq!(year_end.next_after(at(2025, 12, 31, 23, 59)), Some(at(2026, 12, 31, 23, 59)));
+        assert_eq!(CronSchedule::parse("0 0 30 2 *").unwrap().next_after(at(2025, 1, 1, 0, 0)), None);
+
+        for bad in ["* * * *", "60 * * * *", "*/0 * * * *", "5-1 * * * *", "a * * * *"] {
+            assert!(CronSchedule::parse(bad).is_err(), "{}", bad);
+        }
+    }
+}
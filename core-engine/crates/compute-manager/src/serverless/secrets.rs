@@ -0,0 +1,304 @@
+use std::collections::{BTreeMap, HashMap};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sirsi_key_vault::secret::SecretManager;
+use sirsi_key_vault::{Credential, KeyVaultError, SecretRef, SecretString};
+
+use crate::error::{ComputeError, ComputeResult};
+
+// Function environment values written `vault:<secret id>` or `vault:<secret id>#<field>` are
+// key-vault references. Deploys never inline them: the value is copied into the provider's own
+// secret store under `native_secret_name` and the function config points there. Listing maps
+// those pointers back, so only references ever come out of `Function.environment`.
+
+pub const NATIVE_SECRET_PREFIX: &str = "sirsi-";
+pub const LAMBDA_SECRET_SCHEME: &str = "secretsmanager:";
+
+// Azure Key Vault's limit, the tightest of the three
+const MAX_NATIVE_SECRET_NAME: usize = 127;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SecretEnvironment {
+    pub plain: BTreeMap<String, String>,
+    pub secrets: BTreeMap<String, SecretRef>,
+}
+
+impl SecretEnvironment {
+    pub fn parse(environment: &HashMap<String, String>) -> ComputeResult<Self> {
+        let mut parsed = Self::default();
+        for (name, value) in environment {
+            match SecretRef::parse(value) {
+                Some(reference) => {
+                    let native = native_secret_name(&reference);
+                    if native.len() > MAX_NATIVE_SECRET_NAME {
+                        return Err(ComputeError::Validation(format!(
+                            "Secret reference {} in {} is too long for provider secret stores",
+                            reference, name
+                        )));
+                    }
+                    parsed.secrets.insert(name.clone(), reference);
+                }
+                None => {
+                    parsed.plain.insert(name.clone(), value.clone());
+                }
+            }
+        }
+        Ok(parsed)
+    }
+
+    // The environment as configured, references and all
+    pub fn to_environment(&self) -> HashMap<String, String> {
+        let secrets = self.secrets.iter().map(|(name, reference)| (name.clone(), reference.to_string()));
+        self.plain.iter().map(|(name, value)| (name.clone(), value.clone())).chain(secrets).collect()
+    }
+
+    // A variable whose value names one of our native secrets goes back to being a reference
+    fn restore(&mut self, name: &str, value: &str, native: Option<&str>) {
+        match native.and_then(parse_native_secret_name) {
+            Some(reference) => {
+                self.secrets.insert(name.to_string(), reference);
+            }
+            None => {
+                self.plain.insert(name.to_string(), value.to_string());
+            }
+        }
+    }
+}
+
+// Provider secret names allow little beyond letters, digits and dashes, so the reference is
+// escaped reversibly: a dash doubles and any other byte becomes a dash and two hex digits
+pub fn native_secret_name(reference: &SecretRef) -> String {
+    let raw = match &reference.field {
+        Some(field) => format!("{}#{}", reference.secret_id, field),
+        None => reference.secret_id.clone(),
+    };
+    let mut name = NATIVE_SECRET_PREFIX.to_string();
+    for byte in raw.bytes() {
+        match byte {
+            b'-' => name.push_str("--"),
+            b if b.is_ascii_alphanumeric() => name.push(b as char),
+            b => name.push_str(&format!("-{:02x}", b)),
+        }
+    }
+    name
+}
+
+pub fn parse_native_secret_name(name: &str) -> Option<SecretRef> {
+    let escaped = name.strip_prefix(NATIVE_SECRET_PREFIX)?.as_bytes();
+    let mut raw = Vec::with_capacity(escaped.len());
+    let mut i = 0;
+    while i < escaped.len() {
+        match escaped[i] {
+            b'-' if escaped.get(i + 1) == Some(&b'-') => {
+                raw.push(b'-');
+                i += 2;
+            }
+            b'-' => {
+                let hex = std::str::from_utf8(escaped.get(i + 1..i + 3)?).ok()?;
+                raw.push(u8::from_str_radix(hex, 16).ok()?);
+                i += 3;
+            }
+            b if b.is_ascii_alphanumeric() => {
+                raw.push(b);
+                i += 1;
+            }
+            _ => return None,
+        }
+    }
+    let raw = String::from_utf8(raw).ok()?;
+    SecretRef::parse(&format!("vault:{}", raw))
+}
+
+// Where a provider keeps the copies; each provider implements it over its own secret service
+#[async_trait]
+pub trait NativeSecretStore: Send + Sync {
+    // Stores `value` as the latest version of `name`, creating the secret the first time
+    async fn put_secret(&self, name: &str, value: &SecretString) -> ComputeResult<()>;
+}
+
+fn secret_error(reference: &SecretRef, e: KeyVaultError) -> ComputeError {
+    match e {
+        KeyVaultError::NotFound(_) => ComputeError::Config(format!("Function secret {} does not exist", reference)),
+        KeyVaultError::Permission(msg) => ComputeError::Auth(msg),
+        e => ComputeError::Config(format!("Failed to resolve function secret {}: {}", reference, e)),
+    }
+}
+
+pub async fn resolve_secret(reference: &SecretRef, secrets: Option<&dyn SecretManager>) -> ComputeResult<SecretString> {
+    Credential::Vault(reference.clone()).resolve(secrets).await.map_err(|e| secret_error(reference, e))
+}
+
+// Copies the current value of every referenced secret into the provider's store. Deploys call
+// this, so a rotation in key-vault reaches the provider on the next create or update of the
+// function; from there each provider's own refresh rules apply.
+pub async fn sync_secrets(
+    environment: &SecretEnvironment,
+    secrets: Option<&dyn SecretManager>,
+    store: &dyn NativeSecretStore,
+) -> ComputeResult<()> {
+    let references: BTreeMap<String, &SecretRef> = environment.secrets.values().map(|r| (native_secret_name(r), r)).collect();
+    for (name, reference) in references {
+        store.put_secret(&name, &resolve_secret(reference, secrets).await?).await?;
+    }
+    Ok(())
+}
+
+// Lambda has no secret-backed variables, so the variable holds `secretsmanager:<secret name>`
+// for the function to read through the AWS Parameters and Secrets extension. The extension
+// caches for SECRETS_MANAGER_TTL (300 seconds by default); a new version shows up within that
+// without redeploying.
+pub fn lambda_variables(environment: &SecretEnvironment) -> HashMap<String, String> {
+    let secrets = environment
+        .secrets
+        .iter()
+        .map(|(name, reference)| (name.clone(), format!("{}{}", LAMBDA_SECRET_SCHEME, native_secret_name(reference))));
+    environment.plain.clone().into_iter().chain(secrets).collect()
+}
+
+pub fn from_lambda_variables(variables: &HashMap<String, String>) -> SecretEnvironment {
+    let mut environment = SecretEnvironment::default();
+    for (name, value) in variables {
+        environment.restore(name, value, value.strip_prefix(LAMBDA_SECRET_SCHEME));
+    }
+    environment
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CloudRunEnvVar {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value_source: Option<CloudRunValueSource>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CloudRunValueSource {
+    pub secret_key_ref: CloudRunSecretKeyRef,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CloudRunSecretKeyRef {
+    pub secret: String,
+    pub version: String,
+}
+
+// Secret Manager references pinned to `latest`. Cloud Run reads them when an instance starts,
+// so instances started after a new version pick it up and running ones keep the old value
+// until they are replaced.
+pub fn cloud_run_env(environment: &SecretEnvironment) -> Vec<CloudRunEnvVar> {
+    let plain = environment
+        .plain
+        .iter()
+        .map(|(name, value)| CloudRunEnvVar { name: name.clone(), value: Some(value.clone()), value_source: None });
+    let secrets = environment.secrets.iter().map(|(name, reference)| CloudRunEnvVar {
+        name: name.clone(),
+        value: None,
+        value_source: Some(CloudRunValueSource {
+            secret_key_ref: CloudRunSecretKeyRef { secret: native_secret_name(reference), version: "latest".to_string() },
+        }),
+    });
+    plain.chain(secrets).collect()
+}
+
+pub fn from_cloud_run_env(variables: &[CloudRunEnvVar]) -> SecretEnvironment {
+    let mut environment = SecretEnvironment::default();
+    for variable in variables {
+        let native = variable.value_source.as_ref().map(|source| source.secret_key_ref.secret.as_str());
+        environment.restore(&variable.name, variable.value.as_deref().unwrap_or_default(), native);
+    }
+    environment
+}
+
+// Key Vault references without a version. App Service resolves them itself and re-reads the
+// latest version every 24 hours, or straight away on any change to the app's configuration.
+pub fn azure_app_settings(environment: &SecretEnvironment, vault_name: &str) -> HashMap<String, String> {
+    let secrets = environment.secrets.iter().map(|(name, reference)| {
+        let setting = format!("@Microsoft.KeyVault(VaultName={};SecretName={})", vault_name, native_secret_name(reference));
+        (name.clone(), setting)
+    });
+    environment.plain.clone().into_iter().chain(secrets).collect()
+}
+
+pub fn from_azure_app_settings(settings: &HashMap<String, String>) -> SecretEnvironment {
+    let mut environment = SecretEnvironment::default();
+    for (name, value) in settings {
+        let native = value
+            .strip_prefix("@Microsoft.KeyVault(")
+            .and_then(|rest| rest.strip_suffix(')'))
+            .and_then(|rest| rest.split(';').find_map(|part| part.strip_prefix("SecretName=")));
+        environment.restore(name, value, native);
+    }
+    environment
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sirsi_key_vault::secret::InMemorySecretManager;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct RecordingStore {
+        puts: Mutex<Vec<(String, String)>>,
+    }
+
+    #[async_trait]
+    impl NativeSecretStore for RecordingStore {
+        async fn put_secret(&self, name: &str, value: &SecretString) -> ComputeResult<()> {
+            self.puts.lock().unwrap().push((name.to_string(), value.expose().to_string()));
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_provider_payloads_hold_references_not_values() {
+        let secrets = InMemorySecretManager::new();
+        Credential::plaintext(r#"{"user":"app","password":"hunter2-db"}"#).ingest(&secrets, "db/prod").await.unwrap();
+        Credential::plaintext("sk-live-stripe").ingest(&secrets, "stripe-key").await.unwrap();
+        let configured = HashMap::from([
+            ("DB_PASSWORD".to_string(), "vault:db/prod#password".to_string()),
+            ("STRIPE_KEY".to_string(), "vault:stripe-key".to_string()),
+            ("LOG_LEVEL".to_string(), "debug".to_string()),
+        ]);
+        let environment = SecretEnvironment::parse(&configured).unwrap();
+        assert_eq!(native_secret_name(&environment.secrets["DB_PASSWORD"]), "sirsi-db-2fprod-23password");
+        assert_eq!(native_secret_name(&environment.secrets["STRIPE_KEY"]), "sirsi-stripe--key");
+
+        let store = RecordingStore::default();
+        sync_secrets(&environment, Some(&secrets), &store).await.unwrap();
+        assert_eq!(
+            *store.puts.lock().unwrap(),
+            vec![
+                ("sirsi-db-2fprod-23password".to_string(), "hunter2-db".to_string()),
+                ("sirsi-stripe--key".to_string(), "sk-live-stripe".to_string()),
+            ]
+        );
+        assert!(sync_secrets(&environment, None, &store).await.is_err());
+
+        let lambda = lambda_variables(&environment);
+        let cloud_run = cloud_run_env(&environment);
+        let azure = azure_app_settings(&environment, "sirsi-prod");
+        assert_eq!(lambda["DB_PASSWORD"], "secretsmanager:sirsi-db-2fprod-23password");
+        assert_eq!(azure["STRIPE_KEY"], "@Microsoft.KeyVault(VaultName=sirsi-prod;SecretName=sirsi-stripe--key)");
+        let payloads = [
+            serde_json::to_string(&lambda).unwrap(),
+            serde_json::to_string(&cloud_run).unwrap(),
+            serde_json::to_string(&azure).unwrap(),
+        ];
+        for payload in &payloads {
+            assert!(!payload.contains("hunter2") && !payload.contains("sk-live"), "{}", payload);
+            assert!(payload.contains("debug"));
+        }
+
+        // What listing reads back is the configured references
+        assert_eq!(from_lambda_variables(&lambda).to_environment(), configured);
+        assert_eq!(from_cloud_run_env(&cloud_run).to_environment(), configured);
+        assert_eq!(from_azure_app_settings(&azure).to_environment(), configured);
+        let unrelated = HashMap::from([("NOTE".to_string(), "secretsmanager:someone-elses".to_string())]);
+        assert_eq!(from_lambda_variables(&unrelated).to_environment(), unrelated);
+    }
+}
@@ -0,0 +1,587 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::error::{ComputeError, ComputeResult};
+use crate::fleet::InstanceState;
+use crate::provider::Provider;
+use super::{FaultInjection, ServiceMesh, VirtualService};
+
+const DEFAULT_CHECK_INTERVAL_SECONDS: u64 = 10;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ChaosAction {
+    // Delay or abort on the named HTTP route, or on every route when unset
+    MeshFault { virtual_service: String, route: Option<String>, fault: FaultInjection },
+    // Stops `percent` of the group's running instances, capped by the blast radius
+    StopInstances { fleet_id: String, group_id: String, percent: f64 },
+    // Cannot be rolled back; the group's autoscaling is expected to replace them
+    TerminateInstances { fleet_id: String, group_id: String, percent: f64 },
+    // Cuts all ingress and egress for pods matching `selector`
+    IsolateNetwork { namespace: String, selector: HashMap<String, String> },
+}
+
+impl ChaosAction {
+    fn percent(&self) -> Option<f64> {
+        match self {
+            ChaosAction::StopInstances { percent, .. } | ChaosAction::TerminateInstances { percent, .. } => Some(*percent),
+            _ => None,
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            ChaosAction::MeshFault { virtual_service, route, .. } => match route {
+                Some(route) => format!("mesh fault on {}/{}", virtual_service, route),
+                None => format!("mesh fault on {}", virtual_service),
+            },
+            ChaosAction::StopInstances { fleet_id, group_id, percent } => {
+                format!("stop {}% of {}/{}", percent, fleet_id, group_id)
+            }
+            ChaosAction::TerminateInstances { fleet_id, group_id, percent } => {
+                format!("terminate {}% of {}/{}", percent, fleet_id, group_id)
+            }
+            ChaosAction::IsolateNetwork { namespace, .. } => format!("network isolation in {}", namespace),
+        }
+    }
+}
+
+// Must hold before the experiment starts and for as long as it runs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SteadyStateCheck {
+    Slo { slo_id: String },
+    HealthCheck { name: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SteadyStateReading {
+    pub holds: bool,
+    pub detail: String,
+}
+
+// Half-open `[start, end)`; faults are rolled back when the window closes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChaosWindow {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+impl ChaosWindow {
+    pub fn contains(&self, at: DateTime<Utc>) -> bool {
+        at >= self.start && at < self.end
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChaosExperiment {
+    pub id: String,
+    pub name: String,
+    pub actions: Vec<ChaosAction>,
+    // Most of any one target group an action may touch, in percent
+    pub blast_radius_percent: f64,
+    pub steady_state: SteadyStateCheck,
+    pub window: ChaosWindow,
+    pub duration_seconds: u64,
+    #[serde(default = "default_check_interval")]
+    pub check_interval_seconds: u64,
+}
+
+fn default_check_interval() -> u64 {
+    DEFAULT_CHECK_INTERVAL_SECONDS
+}
+
+impl ChaosExperiment {
+    pub fn new(
+        name: impl Into<String>,
+        steady_state: SteadyStateCheck,
+        window: ChaosWindow,
+        duration_seconds: u64,
+    ) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            name: name.into(),
+            actions: Vec::new(),
+            blast_radius_percent: 10.0,
+            steady_state,
+            window,
+            duration_seconds,
+            check_interval_seconds: DEFAULT_CHECK_INTERVAL_SECONDS,
+        }
+    }
+
+    pub fn with_action(mut self, action: ChaosAction) -> Self {
+        self.actions.push(action);
+        self
+    }
+
+    pub fn with_blast_radius(mut self, percent: f64) -> Self {
+        self.blast_radius_percent = percent;
+        self
+    }
+
+    pub fn with_check_interval(mut self, seconds: u64) -> Self {
+        self.check_interval_seconds = seconds;
+        self
+    }
+
+    pub fn validate(&self) -> ComputeResult<()> {
+        if self.actions.is_empty() {
+            return Err(ComputeError::Validation("An experiment needs at least one action".into()));
+        }
+        if !(self.blast_radius_percent > 0.0 && self.blast_radius_percent <= 100.0) {
+            return Err(ComputeError::Validation("Blast radius must be between 0 and 100 percent".into()));
+        }
+        for action in &self.actions {
+            if let Some(percent) = action.percent() {
+                if !(percent > 0.0 && percent <= self.blast_radius_percent) {
+                    return Err(ComputeError::Validation(format!(
+                        "{} exceeds the {}% blast radius",
+                        action.describe(),
+                        self.blast_radius_percent
+                    )));
+                }
+            }
+            if let ChaosAction::IsolateNetwork { selector, .. } = action {
+                if selector.is_empty() {
+                    return Err(ComputeError::Validation("Network isolation needs a pod selector".into()));
+                }
+            }
+        }
+        if self.window.end <= self.window.start {
+            return Err(ComputeError::Validation("The window must end after it starts".into()));
+        }
+        if self.duration_seconds == 0 || self.check_interval_seconds == 0 {
+            return Err(ComputeError::Validation("Duration and check interval must be positive".into()));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimelineKind {
+    SteadyState,
+    ActionApplied,
+    ActionFailed,
+    Abort,
+    RolledBack,
+    RollbackFailed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimelineEntry {
+    pub at: DateTime<Utc>,
+    pub kind: TimelineKind,
+    pub detail: String,
+    #[serde(default)]
+    pub data: Value,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    Completed,
+    // The steady state did not hold, before the start or while running
+    Aborted,
+    // An action could not be applied
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExperimentRun {
+    pub id: String,
+    pub experiment_id: String,
+    pub status: RunStatus,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub timeline: Vec<TimelineEntry>,
+}
+
+impl ExperimentRun {
+    pub fn entries(&self, kind: TimelineKind) -> impl Iterator<Item = &TimelineEntry> {
+        self.timeline.iter().filter(move |e| e.kind == kind)
+    }
+}
+
+// Evaluates the experiment's steady-state check against SLOs or health checks
+#[async_trait]
+pub trait SteadyStateProbe: Send + Sync {
+    async fn check(&self, check: &SteadyStateCheck) -> ComputeResult<SteadyStateReading>;
+}
+
+// The slice of a mesh fault injection needs; `ServiceMesh` itself cannot be used as a trait object
+#[async_trait]
+pub trait VirtualServiceClient: Send + Sync {
+    async fn get_virtual_service(&self, name: &str) -> ComputeResult<VirtualService>;
+    async fn update_virtual_service(&self, service: VirtualService) -> ComputeResult<VirtualService>;
+}
+
+#[async_trait]
+impl<T: ServiceMesh> VirtualServiceClient for T {
+    async fn get_virtual_service(&self, name: &str) -> ComputeResult<VirtualService> {
+        ServiceMesh::get_virtual_service(self, name).await
+    }
+
+    async fn update_virtual_service(&self, service: VirtualService) -> ComputeResult<VirtualService> {
+        ServiceMesh::update_virtual_service(self, service).await
+    }
+}
+
+// Applies and removes a deny-all network policy; returns the policy's name
+#[async_trait]
+pub trait NetworkIsolator: Send + Sync {
+    async fn isolate(&self, namespace: &str, selector: &HashMap<String, String>) -> ComputeResult<String>;
+    async fn remove(&self, namespace: &str, policy: &str) -> ComputeResult<()>;
+}
+
+// How to undo one applied action
+#[derive(Debug, Clone)]
+enum Undo {
+    RestoreVirtualService(VirtualService),
+    StartInstances(Vec<String>),
+    RemovePolicy { namespace: String, policy: String },
+    Irreversible,
+}
+
+pub struct ChaosRunner {
+    probe: Arc<dyn SteadyStateProbe>,
+    mesh: Option<Arc<dyn VirtualServiceClient>>,
+    provider: Option<Arc<dyn Provider>>,
+    network: Option<Arc<dyn NetworkIsolator>>,
+}
+
+impl ChaosRunner {
+    pub fn new(probe: Arc<dyn SteadyStateProbe>) -> Self {
+        Self { probe, mesh: None, provider: None, network: None }
+    }
+
+    pub fn with_mesh(mut self, mesh: Arc<dyn VirtualServiceClient>) -> Self {
+        self.mesh = Some(mesh);
+        self
+    }
+
+    pub fn with_provider(mut self, provider: Arc<dyn Provider>) -> Self {
+        self.provider = Some(provider);
+        self
+    }
+
+    pub fn with_network(mut self, network: Arc<dyn NetworkIsolator>) -> Self {
+        self.network = Some(network);
+        self
+    }
+
+    // Runs to completion: applies the actions, watches the steady state for the experiment's
+    // duration (or until the window closes) and always rolls back what it applied
+    pub async fn run(&self, experiment: &ChaosExperiment) -> ComputeResult<ExperimentRun> {
+        experiment.validate()?;
+        let started_at = Utc::now();
+        if !experiment.window.contains(started_at) {
+            return Err(ComputeError::Conflict(format!("Experiment {} is outside its scheduled window", experiment.id)));
+        }
+        let mut run = ExperimentRun {
+            id: Uuid::new_v4().to_string(),
+            experiment_id: experiment.id.clone(),
+            status: RunStatus::Completed,
+            started_at,
+            finished_at: started_at,
+            timeline: Vec::new(),
+        };
+
+        if !self.observe(experiment, &mut run).await {
+            record(&mut run, TimelineKind::Abort, "Steady state did not hold before the start; nothing was applied", Value::Null);
+            run.status = RunStatus::Aborted;
+            run.finished_at = Utc::now();
+            return Ok(run);
+        }
+
+        let mut undo = Vec::new();
+        for action in &experiment.actions {
+            match self.apply(action, experiment, &run.id).await {
+                Ok((step, data)) => {
+                    record(&mut run, TimelineKind::ActionApplied, &action.describe(), data);
+                    undo.push((action.describe(), step));
+                }
+                Err(e) => {
+                    warn!("Chaos action failed in experiment {}: {}", experiment.id, e);
+                    record(&mut run, TimelineKind::ActionFailed, &format!("{}: {}", action.describe(), e), Value::Null);
+                    run.status = RunStatus::Failed;
+                    break;
+                }
+            }
+        }
+
+        if run.status == RunStatus::Completed {
+            let until_close = (experiment.window.end - started_at).to_std().unwrap_or_default();
+            let deadline = tokio::time::Instant::now() + Duration::from_secs(experiment.duration_seconds).min(until_close);
+            let interval = Duration::from_secs(experiment.check_interval_seconds);
+            while tokio::time::Instant::now() < deadline {
+                let remaining = deadline - tokio::time::Instant::now();
+                tokio::time::sleep(interval.min(remaining)).await;
+                if !self.observe(experiment, &mut run).await {
+                    info!("Aborting experiment {}: steady state violated", experiment.id);
+                    record(&mut run, TimelineKind::Abort, "Steady state violated; rolling back", Value::Null);
+                    run.status = RunStatus::Aborted;
+                    break;
+                }
+            }
+        }
+
+        self.roll_back(undo, &mut run).await;
+        run.finished_at = Utc::now();
+        Ok(run)
+    }
+
+    // A failed probe counts as a violation
+    async fn observe(&self, experiment: &ChaosExperiment, run: &mut ExperimentRun) -> bool {
+        let reading = self.probe.check(&experiment.steady_state).await.unwrap_or_else(|e| SteadyStateReading {
+            holds: false,
+            detail: format!("Steady-state check failed: {}", e),
+        });
+        record(run, TimelineKind::SteadyState, &reading.detail, json!({ "holds": reading.holds }));
+        reading.holds
+    }
+
+    async fn apply(&self, action: &ChaosAction, experiment: &ChaosExperiment, run_id: &str) -> ComputeResult<(Undo, Value)> {
+        match action {
+            ChaosAction::MeshFault { virtual_service, route, fault } => {
+                let mesh = self.mesh.as_ref().ok_or_else(|| ComputeError::Config("No mesh client configured".into()))?;
+                let original = mesh.get_virtual_service(virtual_service).await?;
+                let mut faulted = original.clone();
+                let mut matched = 0;
+                for http in faulted.http_routes.iter_mut().filter(|r| route.as_ref().is_none_or(|name| &r.name == name)) {
+                    http.fault_injection = Some(fault.clone());
+                    matched += 1;
+                }
+                if matched == 0 {
+                    return Err(ComputeError::NotFound(format!("No matching HTTP route on {}", virtual_service)));
+                }
+                mesh.update_virtual_service(faulted).await?;
+                Ok((Undo::RestoreVirtualService(original), json!({ "routes": matched })))
+            }
+            ChaosAction::StopInstances { fleet_id, group_id, percent }
+            | ChaosAction::TerminateInstances { fleet_id, group_id, percent } => {
+                let provider = self.provider.as_ref().ok_or_else(|| ComputeError::Config("No provider configured".into()))?;
+                let running: Vec<String> = provider
+                    .list_instances(fleet_id, Some(group_id))
+                    .await?
+                    .into_iter()
+                    .filter(|i| matches!(i.state, InstanceState::Running))
+                    .map(|i| i.instance_id)
+                    .collect();
+                let targets = sample(&running, percent.min(experiment.blast_radius_percent), run_id);
+                let terminate = matches!(action, ChaosAction::TerminateInstances { .. });
+                let mut touched: Vec<String> = Vec::with_capacity(targets.len());
+                for instance_id in targets {
+                    let result = if terminate {
+                        provider.terminate_instance(&instance_id).await
+                    } else {
+                        provider.stop_instance(&instance_id).await
+                    };
+                    if let Err(e) = result {
+                        // Roll back the instances already stopped before reporting the failure
+                        if !terminate {
+                            for id in &touched {
+                                let _ = provider.start_instance(id).await;
+                            }
+                        }
+                        return Err(e);
+                    }
+                    touched.push(instance_id);
+                }
+                let data = json!({ "instances": touched, "of": running.len() });
+                Ok((if terminate { Undo::Irreversible } else { Undo::StartInstances(touched) }, data))
+            }
+            ChaosAction::IsolateNetwork { namespace, selector } => {
+                let network = self.network.as_ref().ok_or_else(|| ComputeError::Config("No network isolator configured".into()))?;
+                let policy = network.isolate(namespace, selector).await?;
+                Ok((Undo::RemovePolicy { namespace: namespace.clone(), policy: policy.clone() }, json!({ "policy": policy })))
+            }
+        }
+    }
+
+    // Newest first, so stacked faults on one virtual service unwind to the original
+    async fn roll_back(&self, undo: Vec<(String, Undo)>, run: &mut ExperimentRun) {
+        for (action, step) in undo.into_iter().rev() {
+            let result = match &step {
+                Undo::RestoreVirtualService(original) => match &self.mesh {
+                    Some(mesh) => mesh.update_virtual_service(original.clone()).await.map(|_| ()),
+                    None => Ok(()),
+                },
+                Undo::StartInstances(instances) => match &self.provider {
+                    Some(provider) => {
+                        let mut result = Ok(());
+                        for id in instances {
+                            if let Err(e) = provider.start_instance(id).await {
+                                result = Err(e);
+                            }
+                        }
+                        result
+                    }
+                    None => Ok(()),
+                },
+                Undo::RemovePolicy { namespace, policy } => match &self.network {
+                    Some(network) => network.remove(namespace, policy).await,
+                    None => Ok(()),
+                },
+                Undo::Irreversible => {
+                    record(run, TimelineKind::RolledBack, &format!("{}: not reversible", action), Value::Null);
+                    continue;
+                }
+            };
+            match result {
+                Ok(()) => record(run, TimelineKind::RolledBack, &action, Value::Null),
+                Err(e) => {
+                    warn!("Chaos rollback failed for {}: {}", action, e);
+                    record(run, TimelineKind::RollbackFailed, &format!("{}: {}", action, e), Value::Null);
+                }
+            }
+        }
+    }
+}
+
+fn record(run: &mut ExperimentRun, kind: TimelineKind, detail: &str, data: Value) {
+    run.timeline.push(TimelineEntry { at: Utc::now(), kind, detail: detail.to_string(), data });
+}
+
+// A per-run pseudo-random subset, never more than `percent` of the targets
+fn sample(targets: &[String], percent: f64, seed: &str) -> Vec<String> {
+    let count = (targets.len() as f64 * percent / 100.0).floor() as usize;
+    let mut ranked: Vec<(Vec<u8>, &String)> = targets
+        .iter()
+        .map(|t| (Sha256::digest(format!("{}:{}", seed, t).as_bytes()).to_vec(), t))
+        .collect();
+    ranked.sort();
+    ranked.into_iter().take(count).map(|(_, t)| t.clone()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{Abort, HttpRoute, RouteDestination};
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+
+    struct ScriptedProbe(Mutex<VecDeque<bool>>);
+
+    #[async_trait]
+    impl SteadyStateProbe for ScriptedProbe {
+        async fn check(&self, _: &SteadyStateCheck) -> ComputeResult<SteadyStateReading> {
+            let holds = self.0.lock().unwrap().pop_front().unwrap_or(true);
+            Ok(SteadyStateReading { holds, detail: format!("error rate {}", if holds { "0.1%" } else { "12%" }) })
+        }
+    }
+
+    #[derive(Default)]
+    struct FakeMesh {
+        services: Mutex<HashMap<String, VirtualService>>,
+        updates: Mutex<Vec<VirtualService>>,
+    }
+
+    #[async_trait]
+    impl VirtualServiceClient for FakeMesh {
+        async fn get_virtual_service(&self, name: &str) -> ComputeResult<VirtualService> {
+            self.services.lock().unwrap().get(name).cloned().ok_or_else(|| ComputeError::NotFound(name.to_string()))
+        }
+
+        async fn update_virtual_service(&self, service: VirtualService) -> ComputeResult<VirtualService> {
+            self.updates.lock().unwrap().push(service.clone());
+            self.services.lock().unwrap().insert(service.name.clone(), service.clone());
+            Ok(service)
+        }
+    }
+
+    fn checkout() -> VirtualService {
+        let route = |name: &str| HttpRoute {
+            name: name.to_string(),
+            match_rules: Vec::new(),
+            route: vec![RouteDestination { host: "checkout".into(), subset: None, port: Some(8080), weight: 100 }],
+            retry_policy: None,
+            timeout: Some("2s".into()),
+            fault_injection: None,
+            mirror: None,
+        };
+        VirtualService {
+            name: "checkout".into(),
+            hosts: vec!["checkout".into()],
+            gateways: Vec::new(),
+            http_routes: vec![route("api"), route("static")],
+            tcp_routes: Vec::new(),
+        }
+    }
+
+    fn fixture(probe_results: Vec<bool>) -> (ChaosRunner, Arc<FakeMesh>, ChaosExperiment) {
+        let mesh = Arc::new(FakeMesh::default());
+        mesh.services.lock().unwrap().insert("checkout".into(), checkout());
+        let runner = ChaosRunner::new(Arc::new(ScriptedProbe(Mutex::new(probe_results.into())))).with_mesh(mesh.clone());
+        let now = Utc::now();
+        let window = ChaosWindow { start: now - chrono::Duration::minutes(1), end: now + chrono::Duration::hours(1) };
+        let experiment = ChaosExperiment::new("checkout aborts", SteadyStateCheck::Slo { slo_id: "checkout-availability".into() }, window, 60)
+            .with_check_interval(10)
+            .with_action(ChaosAction::MeshFault {
+                virtual_service: "checkout".into(),
+                route: Some("api".into()),
+                fault: FaultInjection { delay: None, abort: Some(Abort { percent: 5.0, http_status: 503 }) },
+            });
+        (runner, mesh, experiment)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_steady_state_violation_aborts_and_restores_virtual_service() {
+        // Holds before the start and at the first check, then breaks
+        let (runner, mesh, experiment) = fixture(vec![true, true, false]);
+        let original = serde_json::to_value(checkout()).unwrap();
+
+        let run = runner.run(&experiment).await.unwrap();
+        assert_eq!(run.status, RunStatus::Aborted);
+        assert_eq!(run.entries(TimelineKind::SteadyState).count(), 3);
+        assert_eq!(run.entries(TimelineKind::ActionApplied).count(), 1);
+        assert_eq!(run.entries(TimelineKind::Abort).count(), 1);
+        assert_eq!(run.entries(TimelineKind::RolledBack).count(), 1);
+        let kinds: Vec<TimelineKind> = run.timeline.iter().map(|e| e.kind).collect();
+        assert_eq!(kinds.last(), Some(&TimelineKind::RolledBack));
+
+        let updates = mesh.updates.lock().unwrap().clone();
+        assert_eq!(updates.len(), 2);
+        assert!(updates[0].http_routes[0].fault_injection.is_some());
+        assert!(updates[0].http_routes[1].fault_injection.is_none());
+        let restored = mesh.get_virtual_service("checkout").await.unwrap();
+        assert_eq!(serde_json::to_value(restored).unwrap(), original);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_guards_before_anything_is_applied() {
+        // The steady state doesn't hold to begin with
+        let (runner, mesh, experiment) = fixture(vec![false]);
+        let run = runner.run(&experiment).await.unwrap();
+        assert_eq!(run.status, RunStatus::Aborted);
+        assert_eq!(run.entries(TimelineKind::ActionApplied).count(), 0);
+        assert!(mesh.updates.lock().unwrap().is_empty());
+
+        // Blast radius and window
+        let (runner, _, experiment) = fixture(Vec::new());
+        let wide = experiment.clone().with_action(ChaosAction::StopInstances {
+            fleet_id: "web".into(),
+            group_id: "canary".into(),
+            percent: 50.0,
+        });
+        assert!(matches!(wide.validate(), Err(ComputeError::Validation(_))));
+        let mut closed = experiment;
+        closed.window.end = closed.window.start + chrono::Duration::seconds(1);
+        assert!(matches!(runner.run(&closed).await, Err(ComputeError::Conflict(_))));
+
+        let targets: Vec<String> = (0..20).map(|i| format!("i-{}", i)).collect();
+        assert_eq!(sample(&targets, 10.0, "run").len(), 2);
+        assert_eq!(sample(&targets, 4.0, "run").len(), 0);
+        assert_eq!(sample(&targets, 10.0, "run"), sample(&targets, 10.0, "run"));
+    }
+}
@@ -1,5 +1,7 @@
 use async_trait::async_trait;
+use serde_json::{json, Value};
 use std::sync::Arc;
+use chrono::Utc;
 use k8s_openapi::api::networking::v1beta1::{
     VirtualService as K8sVirtualService,
     DestinationRule,
@@ -10,15 +12,17 @@ use k8s_openapi::api::security::v1beta1::{
     PeerAuthentication,
 };
 use kube::{
-    api::{Api, ListParams, PostParams},
+    api::{Api, ApiResource, DeleteParams, DynamicObject, GroupVersionKind, ListParams, Patch, PatchParams, PostParams},
     client::Client,
     config::KubeConfig,
     ResourceExt,
 };
 
 use crate::error::{ComputeError, ComputeResult};
+use super::mtls::{CertificateCollector, CliCertificateCollector, MtlsStatusReport};
+use super::onboarding::{MeshClusterClient, NamespaceOnboarder, OnboardingOptions, OnboardingReport, ResourceKey};
 use super::{
-    ServiceMesh, ServiceMeshConfig, VirtualService, TrafficPolicy,
+    MeshType, ServiceMesh, ServiceMeshConfig, VirtualService, HttpRoute, RouteDestination, TrafficPolicy,
     CircuitBreakerConfig, RetryPolicy, AuthorizationPolicy, AuthenticationPolicy,
     TracingConfig, MetricsConfig, ServiceMeshMetrics, Trace
 };
@@ -26,6 +30,8 @@ use super::{
 pub struct IstioProvider {
     client: Client,
     config: ServiceMeshConfig,
+    certificates: Arc<dyn CertificateCollector>,
+    cluster: Arc<dyn MeshClusterClient>,
 }
 
 #[async_trait]
@@ -35,7 +41,12 @@ impl ServiceMesh for IstioProvider {
             .await
             .map_err(|e| ComputeError::Provider(format!("Failed to create Kubernetes client: {}", e)))?;
 
-        Ok(Box::new(Self { client, config }))
+        Ok(Box::new(Self {
+            cluster: Arc::new(KubeMeshClient::new(client.clone())),
+            client,
+            config,
+            certificates: Arc::new(CliCertificateCollector::new(MeshType::Istio)),
+        }))
     }
 
     async fn get_config(&self) -> ComputeResult<ServiceMeshConfig> {
@@ -153,6 +164,19 @@ impl ServiceMesh for IstioProvider {
         Ok(())
     }
 
+    async fn get_mtls_status(&self, namespace: &str) -> ComputeResult<MtlsStatusReport> {
+        let collected = self.certificates.collect(namespace).await?;
+        Ok(MtlsStatusReport::build(namespace, collected, self.config.mtls_thresholds, Utc::now()))
+    }
+
+    async fn onboard_namespace(&self, namespace: &str, options: OnboardingOptions) -> ComputeResult<OnboardingReport> {
+        NamespaceOnboarder::new(self.cluster.clone()).onboard(namespace, &options).await
+    }
+
+    async fn offboard_namespace(&self, namespace: &str) -> ComputeResult<OnboardingReport> {
+        NamespaceOnboarder::new(self.cluster.clone()).offboard(namespace).await
+    }
+
     async fn create_authorization_policy(&self, policy: AuthorizationPolicy) -> ComputeResult<()> {
         let api: Api<K8sAuthorizationPolicy> = Api::namespaced(self.client.clone(), &policy.namespace);
 
@@ -187,8 +211,21 @@ impl ServiceMesh for IstioProvider {
 }
 
 impl IstioProvider {
+    // Replaces the CLI-based collector, e.g. with one reading proxy admin endpoints directly
+    pub fn with_certificate_collector(mut self, collector: Arc<dyn CertificateCollector>) -> Self {
+        self.certificates = collector;
+        self
+    }
+
+    pub fn with_cluster_client(mut self, cluster: Arc<dyn MeshClusterClient>) -> Self {
+        self.cluster = cluster;
+        self
+    }
+
     fn convert_to_k8s_virtual_service(&self, service: &VirtualService) -> ComputeResult<K8sVirtualService> {
-        unimplemented!("Conversion to Kubernetes VirtualService not yet implemented")
+        service.validate()?;
+        serde_json::from_value(virtual_service_manifest(service, &self.config.namespace))
+            .map_err(|e| ComputeError::Internal(format!("Failed to build virtual service {}: {}", service.name, e)))
     }
 
     fn convert_from_k8s_virtual_service(&self, k8s_vs: &K8sVirtualService) -> ComputeResult<VirtualService> {
@@ -208,6 +245,233 @@ impl IstioProvider {
     }
 }
 
+const FIELD_MANAGER: &str = "sirsi-mesh-onboarding";
+const ROLLOUT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(300);
+
+// MeshClusterClient over the dynamic Kubernetes API
+pub struct KubeMeshClient {
+    client: Client,
+}
+
+impl KubeMeshClient {
+    pub fn new(client: Client) -> Self {
+        Self { client }
+    }
+
+    fn api(&self, api_version: &str, kind: &str, namespace: Option<&str>) -> Api<DynamicObject> {
+        let (group, version) = api_version.split_once('/').unwrap_or(("", api_version));
+        let plural = match kind.to_ascii_lowercase() {
+            k if k.ends_with('y') => format!("{}ies", &k[..k.len() - 1]),
+            k => format!("{}s", k),
+        };
+        let resource = ApiResource::from_gvk_with_plural(&GroupVersionKind::gvk(group, version, kind), &plural);
+        match namespace {
+            Some(ns) => Api::namespaced_with(self.client.clone(), ns, &resource),
+            None => Api::all_with(self.client.clone(), &resource),
+        }
+    }
+
+    fn key_api(&self, key: &ResourceKey) -> Api<DynamicObject> {
+        self.api(&key.api_version, &key.kind, key.namespace.as_deref())
+    }
+}
+
+fn kube_error(action: &str, target: impl std::fmt::Display, e: kube::Error) -> ComputeError {
+    ComputeError::Provider(format!("Failed to {} {}: {}", action, target, e))
+}
+
+fn to_value(object: DynamicObject) -> ComputeResult<Value> {
+    serde_json::to_value(object).map_err(|e| ComputeError::Internal(format!("Unserializable Kubernetes object: {}", e)))
+}
+
+#[async_trait]
+impl MeshClusterClient for KubeMeshClient {
+    async fn get(&self, key: &ResourceKey) -> ComputeResult<Option<Value>> {
+        match self.key_api(key).get_opt(&key.name).await {
+            Ok(object) => object.map(to_value).transpose(),
+            Err(e) => Err(kube_error("get", key, e)),
+        }
+    }
+
+    async fn list(&self, namespace: &str, api_version: &str, kind: &str, label_selector: Option<&str>) -> ComputeResult<Vec<Value>> {
+        let mut params = ListParams::default();
+        if let Some(selector) = label_selector {
+            params = params.labels(selector);
+        }
+        let objects = self
+            .api(api_version, kind, Some(namespace))
+            .list(&params)
+            .await
+            .map_err(|e| kube_error("list", kind, e))?;
+        objects.items.into_iter().map(to_value).collect()
+    }
+
+    async fn apply(&self, manifest: Value) -> ComputeResult<()> {
+        let key = ResourceKey::of(&manifest)?;
+        self.key_api(&key)
+            .patch(&key.name, &PatchParams::apply(FIELD_MANAGER).force(), &Patch::Apply(&manifest))
+            .await
+            .map_err(|e| kube_error("apply", &key, e))?;
+        Ok(())
+    }
+
+    async fn delete(&self, key: &ResourceKey) -> ComputeResult<bool> {
+        match self.key_api(key).delete(&key.name, &DeleteParams::default()).await {
+            Ok(_) => Ok(true),
+            Err(kube::Error::Api(response)) if response.code == 404 => Ok(false),
+            Err(e) => Err(kube_error("delete", key, e)),
+        }
+    }
+
+    async fn patch(&self, key: &ResourceKey, patch: Value) -> ComputeResult<()> {
+        self.key_api(key)
+            .patch(&key.name, &PatchParams::default(), &Patch::Merge(&patch))
+            .await
+            .map_err(|e| kube_error("patch", key, e))?;
+        Ok(())
+    }
+
+    // Same trigger as `kubectl rollout restart`, then waits for a pod created after it to run
+    async fn restart_deployment(&self, namespace: &str, name: &str) -> ComputeResult<String> {
+        let key = ResourceKey::new("apps/v1", "Deployment", Some(namespace), name);
+        let restarted_at = Utc::now();
+        let patch = json!({ "spec": { "template": { "metadata": { "annotations": {
+            "kubectl.kubernetes.io/restartedAt": restarted_at.to_rfc3339(),
+        } } } } });
+        self.patch(&key, patch).await?;
+
+        let deployment = self.get(&key).await?.ok_or_else(|| ComputeError::NotFound(key.to_string()))?;
+        let selector = deployment["spec"]["selector"]["matchLabels"]
+            .as_object()
+            .map(|labels| {
+                labels
+                    .iter()
+                    .map(|(k, v)| format!("{}={}", k, v.as_str().unwrap_or_default()))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            })
+            .unwrap_or_default();
+        let deadline = tokio::time::Instant::now() + ROLLOUT_TIMEOUT;
+        while tokio::time::Instant::now() < deadline {
+            let pods = self.list(namespace, "v1", "Pod", Some(&selector)).await?;
+            let fresh = pods.iter().find(|pod| {
+                let created = pod["metadata"]["creationTimestamp"]
+                    .as_str()
+                    .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok());
+                created.is_some_and(|t| t >= restarted_at - chrono::Duration::seconds(1))
+                    && pod["status"]["phase"] == "Running"
+                    && pod["metadata"]["deletionTimestamp"].is_null()
+            });
+            if let Some(pod) = fresh {
+                return Ok(pod["metadata"]["name"].as_str().unwrap_or_default().to_string());
+            }
+            tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+        }
+        Err(ComputeError::Unavailable(format!("Deployment {} did not roll out within {:?}", name, ROLLOUT_TIMEOUT)))
+    }
+
+    // The server-side sidecar only forwards an X-Forwarded-Client-Cert header for mTLS requests
+    async fn probe_mtls(&self, namespace: &str, pod: &str, target: &str) -> ComputeResult<bool> {
+        let url = format!("http://{}/headers", target);
+        let output = tokio::process::Command::new("kubectl")
+            .args(["exec", "-n", namespace, pod, "--", "curl", "-sS", "--max-time", "10", &url])
+            .output()
+            .await
+            .map_err(|e| ComputeError::Provider(format!("Failed to run kubectl exec: {}", e)))?;
+        if !output.status.success() {
+            return Err(ComputeError::Provider(format!(
+                "Probe from {} to {} failed: {}",
+                pod,
+                target,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).contains("X-Forwarded-Client-Cert"))
+    }
+}
+
+fn destination(host: &str, subset: Option<&String>, port: Option<i32>) -> Value {
+    let mut dest = json!({ "host": host });
+    if let Some(subset) = subset {
+        dest["subset"] = json!(subset);
+    }
+    if let Some(port) = port {
+        dest["port"] = json!({ "number": port });
+    }
+    dest
+}
+
+fn http_route_manifest(route: &HttpRoute) -> Value {
+    let routes: Vec<Value> = route
+        .route
+        .iter()
+        .map(|d: &RouteDestination| {
+            json!({
+                "destination": destination(&d.host, d.subset.as_ref(), d.port),
+                "weight": d.weight,
+            })
+        })
+        .collect();
+    let matches: Vec<Value> = route
+        .match_rules
+        .iter()
+        .map(|m| {
+            let mut rule = json!({});
+            if let Some(prefix) = &m.uri_prefix {
+                rule["uri"] = json!({ "prefix": prefix });
+            } else if let Some(exact) = &m.uri_exact {
+                rule["uri"] = json!({ "exact": exact });
+            } else if let Some(regex) = &m.uri_regex {
+                rule["uri"] = json!({ "regex": regex });
+            }
+            if !m.headers.is_empty() {
+                rule["headers"] = m.headers.iter().map(|(k, v)| (k.clone(), json!({ "exact": v }))).collect();
+            }
+            if !m.query_params.is_empty() {
+                rule["queryParams"] = m.query_params.iter().map(|(k, v)| (k.clone(), json!({ "exact": v }))).collect();
+            }
+            if let Some(method) = &m.method {
+                rule["method"] = json!({ "exact": method });
+            }
+            rule
+        })
+        .collect();
+
+    let mut manifest = json!({ "name": route.name, "route": routes });
+    if !matches.is_empty() {
+        manifest["match"] = json!(matches);
+    }
+    if let Some(timeout) = &route.timeout {
+        manifest["timeout"] = json!(timeout);
+    }
+    if let Some(retry) = &route.retry_policy {
+        manifest["retries"] = json!({
+            "attempts": retry.attempts,
+            "perTryTimeout": retry.per_try_timeout,
+            "retryOn": retry.retry_on.join(","),
+        });
+    }
+    if let Some(mirror) = &route.mirror {
+        // Envoy sends the shadow copy with "-shadow" appended to the Host and drops its response
+        manifest["mirror"] = destination(&mirror.host, mirror.subset.as_ref(), mirror.port);
+        manifest["mirrorPercentage"] = json!({ "value": mirror.percentage });
+    }
+    manifest
+}
+
+pub(crate) fn virtual_service_manifest(service: &VirtualService, namespace: &str) -> Value {
+    json!({
+        "apiVersion": "networking.istio.io/v1beta1",
+        "kind": "VirtualService",
+        "metadata": { "name": service.name, "namespace": namespace },
+        "spec": {
+            "hosts": service.hosts,
+            "gateways": service.gateways,
+            "http": service.http_routes.iter().map(http_route_manifest).collect::<Vec<_>>(),
+        },
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -226,18 +490,39 @@ mod tests {
         }
     }
 
-    #[tokio::test]
-    #[should_panic(expected = "Conversion to Kubernetes VirtualService not yet implemented")]
-    async fn test_create_virtual_service() {
-        let provider = setup_test_provider().await.unwrap();
+    #[test]
+    fn test_virtual_service_manifest_mirror() {
         let service = VirtualService {
             name: "test-service".to_string(),
             hosts: vec!["example.com".to_string()],
             gateways: vec!["test-gateway".to_string()],
-            http_routes: Vec::new(),
+            http_routes: vec![HttpRoute {
+                name: "primary".to_string(),
+                match_rules: Vec::new(),
+                route: vec![RouteDestination {
+                    host: "checkout".to_string(),
+                    subset: Some("v1".to_string()),
+                    port: Some(8080),
+                    weight: 100,
+                }],
+                retry_policy: None,
+                timeout: None,
+                fault_injection: None,
+                mirror: Some(super::super::HttpMirror {
+                    host: "checkout".to_string(),
+                    subset: Some("v2".to_string()),
+                    port: None,
+                    percentage: 25.0,
+                }),
+            }],
             tcp_routes: Vec::new(),
         };
-        provider.create_virtual_service(service).await.unwrap();
+
+        let manifest = virtual_service_manifest(&service, "default");
+        let route = &manifest["spec"]["http"][0];
+        assert_eq!(route["route"][0]["destination"]["subset"], "v1");
+        assert_eq!(route["mirror"], json!({ "host": "checkout", "subset": "v2" }));
+        assert_eq!(route["mirrorPercentage"]["value"], 25.0);
     }
 
     #[tokio::test]
@@ -1,5 +1,6 @@
 use async_trait::async_trait;
 use std::sync::Arc;
+use chrono::Utc;
 use k8s_openapi::api::core::v1::{Service, ServiceSpec};
 use kube::{
     api::{Api, ListParams, PostParams},
@@ -9,8 +10,10 @@ use kube::{
 };
 
 use crate::error::{ComputeError, ComputeResult};
+use super::mtls::{CertificateCollector, CliCertificateCollector, MtlsStatusReport};
+use super::onboarding::{OnboardingOptions, OnboardingReport};
 use super::{
-    ServiceMesh, ServiceMeshConfig, VirtualService, TrafficPolicy,
+    MeshType, ServiceMesh, ServiceMeshConfig, VirtualService, TrafficPolicy,
     CircuitBreakerConfig, RetryPolicy, AuthorizationPolicy, AuthenticationPolicy,
     TracingConfig, MetricsConfig, ServiceMeshMetrics, Trace,
 };
@@ -23,6 +26,7 @@ const LINKERD_TIMEOUT_ANNOTATION: &str = "config.linkerd.io/timeout";
 pub struct LinkerdProvider {
     client: Client,
     config: ServiceMeshConfig,
+    certificates: Arc<dyn CertificateCollector>,
 }
 
 #[async_trait]
@@ -32,7 +36,11 @@ impl ServiceMesh for LinkerdProvider {
             .await
             .map_err(|e| ComputeError::Provider(format!("Failed to create Kubernetes client: {}", e)))?;
 
-        Ok(Box::new(Self { client, config }))
+        Ok(Box::new(Self {
+            client,
+            config,
+            certificates: Arc::new(CliCertificateCollector::new(MeshType::Linkerd)),
+        }))
     }
 
     async fn get_config(&self) -> ComputeResult<ServiceMeshConfig> {
@@ -220,6 +228,26 @@ impl ServiceMesh for LinkerdProvider {
         Ok(())
     }
 
+    async fn get_mtls_status(&self, namespace: &str) -> ComputeResult<MtlsStatusReport> {
+        let collected = self.certificates.collect(namespace).await?;
+        Ok(MtlsStatusReport::build(namespace, collected, self.config.mtls_thresholds, Utc::now()))
+    }
+
+    // Onboarding provisions Istio security and telemetry resources, which Linkerd has no equivalent for
+    async fn onboard_namespace(&self, namespace: &str, _options: OnboardingOptions) -> ComputeResult<OnboardingReport> {
+        Err(ComputeError::Validation(format!(
+            "Namespace onboarding for {} is not supported on Linkerd",
+            namespace
+        )))
+    }
+
+    async fn offboard_namespace(&self, namespace: &str) -> ComputeResult<OnboardingReport> {
+        Err(ComputeError::Validation(format!(
+            "Namespace offboarding for {} is not supported on Linkerd",
+            namespace
+        )))
+    }
+
     async fn create_authorization_policy(&self, policy: AuthorizationPolicy) -> ComputeResult<()> {
         // Linkerd uses RBAC for authorization, which needs to be handled differently
         unimplemented!("Linkerd provider create_authorization_policy not yet implemented")
@@ -247,7 +275,21 @@ impl ServiceMesh for LinkerdProvider {
 }
 
 impl LinkerdProvider {
+    // Replaces the CLI-based collector, e.g. with one reading proxy admin endpoints directly
+    pub fn with_certificate_collector(mut self, collector: Arc<dyn CertificateCollector>) -> Self {
+        self.certificates = collector;
+        self
+    }
+
     fn create_service_from_virtual_service(&self, vs: &VirtualService) -> ComputeResult<Service> {
+        vs.validate()?;
+        // Annotations have no way to express a shadow destination; refuse rather than drop it
+        if let Some(route) = vs.http_routes.iter().find(|r| r.mirror.is_some()) {
+            return Err(ComputeError::Validation(format!(
+                "Route {} requests traffic mirroring, which the Linkerd backend does not support",
+                route.name
+            )));
+        }
         let mut annotations = std::collections::HashMap::new();
         annotations.insert(LINKERD_INJECT_ANNOTATION.to_string(), "enabled".to_string());
 
@@ -313,6 +355,7 @@ impl LinkerdProvider {
                         retry_policy: None,
                         timeout: None,
                         fault_injection: None,
+                        mirror: None,
                     };
                     vs.http_routes.push(route);
                 }
@@ -436,6 +479,7 @@ mod tests {
                     }),
                     timeout: Some("5s".to_string()),
                     fault_injection: None,
+                    mirror: None,
                 },
             ],
             tcp_routes: Vec::new(),
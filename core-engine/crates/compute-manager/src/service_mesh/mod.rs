@@ -2,7 +2,15 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use async_trait::async_trait;
 
-use crate::error::ComputeResult;
+use crate::error::{ComputeError, ComputeResult};
+
+pub mod chaos;
+pub mod mtls;
+pub mod onboarding;
+
+pub use chaos::{ChaosAction, ChaosExperiment, ChaosRunner, ChaosWindow, ExperimentRun, SteadyStateCheck, SteadyStateProbe};
+pub use mtls::{CertificateCollector, CliCertificateCollector, MtlsStatusReport, MtlsThresholds};
+pub use onboarding::{MeshClusterClient, NamespaceOnboarder, OnboardingOptions, OnboardingReport};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServiceMeshConfig {
@@ -15,6 +23,8 @@ pub struct ServiceMeshConfig {
     pub egress_gateway: Option<EgressGateway>,
     pub labels: HashMap<String, String>,
     pub annotations: HashMap<String, String>,
+    #[serde(default)]
+    pub mtls_thresholds: MtlsThresholds,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -127,6 +137,17 @@ pub struct HttpRoute {
     pub retry_policy: Option<RetryPolicy>,
     pub timeout: Option<String>,
     pub fault_injection: Option<FaultInjection>,
+    #[serde(default)]
+    pub mirror: Option<HttpMirror>,
+}
+
+// Shadow copy of a route's traffic; the mesh discards the mirror's responses
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HttpMirror {
+    pub host: String,
+    pub subset: Option<String>,
+    pub port: Option<i32>,
+    pub percentage: f64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -220,6 +241,10 @@ pub trait ServiceMesh: Send + Sync + 'static {
     // Security
     async fn enable_mtls(&self, namespace: &str) -> ComputeResult<()>;
     async fn disable_mtls(&self, namespace: &str) -> ComputeResult<()>;
+    // Per-workload certificate health, judged against `mtls_thresholds`
+    async fn get_mtls_status(&self, namespace: &str) -> ComputeResult<MtlsStatusReport>;
+    async fn onboard_namespace(&self, namespace: &str, options: OnboardingOptions) -> ComputeResult<OnboardingReport>;
+    async fn offboard_namespace(&self, namespace: &str) -> ComputeResult<OnboardingReport>;
     async fn create_authorization_policy(&self, policy: AuthorizationPolicy) -> ComputeResult<()>;
     async fn create_authentication_policy(&self, policy: AuthenticationPolicy) -> ComputeResult<()>;
 
@@ -402,6 +427,7 @@ impl ServiceMeshConfig {
             egress_gateway: None,
             labels: HashMap::new(),
             annotations: HashMap::new(),
+            mtls_thresholds: MtlsThresholds::default(),
         }
     }
 
@@ -416,6 +442,43 @@ impl ServiceMeshConfig {
     }
 }
 
+impl VirtualService {
+    pub fn validate(&self) -> ComputeResult<()> {
+        for route in &self.http_routes {
+            route.validate()?;
+        }
+        Ok(())
+    }
+}
+
+impl HttpRoute {
+    pub fn validate(&self) -> ComputeResult<()> {
+        let Some(mirror) = &self.mirror else {
+            return Ok(());
+        };
+        if !(mirror.percentage > 0.0 && mirror.percentage <= 100.0) {
+            return Err(ComputeError::Validation(format!(
+                "Route {} mirror percentage must be in (0, 100], got {}",
+                self.name, mirror.percentage
+            )));
+        }
+        // Mirroring into a destination the route already serves would double its live traffic
+        if let Some(dest) = self
+            .route
+            .iter()
+            .find(|d| d.host == mirror.host && d.subset == mirror.subset)
+        {
+            return Err(ComputeError::Validation(format!(
+                "Route {} mirrors to {}{} which it also routes to",
+                self.name,
+                dest.host,
+                dest.subset.as_deref().map(|s| format!(" subset {}", s)).unwrap_or_default()
+            )));
+        }
+        Ok(())
+    }
+}
+
 impl IngressGateway {
     pub fn new(name: String) -> Self {
         Self {
@@ -519,6 +582,7 @@ mod tests {
                     }),
                     timeout: Some("5s".to_string()),
                     fault_injection: None,
+                    mirror: None,
                 },
             ],
             tcp_routes: Vec::new(),
@@ -528,4 +592,44 @@ mod tests {
         assert_eq!(service.http_routes.len(), 1);
         assert!(service.http_routes[0].retry_policy.is_some());
     }
+
+    fn mirrored_route(subset: Option<&str>, percentage: f64) -> HttpRoute {
+        HttpRoute {
+            name: "checkout".to_string(),
+            match_rules: vec![],
+            route: vec![RouteDestination {
+                host: "checkout".to_string(),
+                subset: Some("v1".to_string()),
+                port: None,
+                weight: 100,
+            }],
+            retry_policy: None,
+            timeout: None,
+            fault_injection: None,
+            mirror: Some(HttpMirror {
+                host: "checkout".to_string(),
+                subset: subset.map(str::to_string),
+                port: None,
+                percentage,
+            }),
+        }
+    }
+
+    #[test]
+    fn test_mirror_validation() {
+        assert!(mirrored_route(Some("v2"), 10.0).validate().is_ok());
+        assert!(mirrored_route(None, 100.0).validate().is_ok());
+        assert!(matches!(
+            mirrored_route(Some("v1"), 10.0).validate(),
+            Err(ComputeError::Validation(_))
+        ));
+        assert!(matches!(
+            mirrored_route(Some("v2"), 0.0).validate(),
+            Err(ComputeError::Validation(_))
+        ));
+        assert!(matches!(
+            mirrored_route(Some("v2"), 150.0).validate(),
+            Err(ComputeError::Validation(_))
+        ));
+    }
 }
@@ -0,0 +1,485 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sirsi_observability::monitoring::{AlertEvent, AlertSeverity, AlertState};
+use tracing::warn;
+
+use crate::error::{ComputeError, ComputeResult};
+use super::MeshType;
+
+pub const MTLS_ALERT_RULE_ID: &str = "service-mesh-mtls-certificates";
+
+// Workload certificates in both Istio and Linkerd default to a 24h lifetime
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct MtlsThresholds {
+    pub warning_seconds: i64,
+    pub critical_seconds: i64,
+    // Both meshes renew around the half-life; a certificate this far into its lifetime has missed it
+    pub rotation_overdue_fraction: f64,
+}
+
+impl Default for MtlsThresholds {
+    fn default() -> Self {
+        Self {
+            warning_seconds: 6 * 3600,
+            critical_seconds: 3600,
+            rotation_overdue_fraction: 0.8,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkloadCertificate {
+    pub workload: String,
+    pub namespace: String,
+    pub serial_number: Option<String>,
+    pub issuer: Option<String>,
+    pub issued_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    // Set when the proxy itself reports a problem, e.g. Istio's cert chain not ACTIVE or invalid
+    pub rotation_error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollectionFailure {
+    pub workload: String,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CollectedCertificates {
+    pub certificates: Vec<WorkloadCertificate>,
+    pub failures: Vec<CollectionFailure>,
+}
+
+// Source of workload certificate data; the CLI collector is the default, tests and
+// environments with proxy admin access can plug in their own
+#[async_trait]
+pub trait CertificateCollector: Send + Sync {
+    async fn collect(&self, namespace: &str) -> ComputeResult<CollectedCertificates>;
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RotationHealth {
+    Healthy,
+    Overdue,
+    Failing(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkloadMtlsStatus {
+    pub certificate: WorkloadCertificate,
+    pub seconds_to_expiry: i64,
+    pub rotation: RotationHealth,
+}
+
+impl WorkloadMtlsStatus {
+    fn evaluate(certificate: WorkloadCertificate, thresholds: &MtlsThresholds, now: DateTime<Utc>) -> Self {
+        let seconds_to_expiry = (certificate.expires_at - now).num_seconds();
+        let lifetime = (certificate.expires_at - certificate.issued_at).num_seconds().max(1);
+        let elapsed = (now - certificate.issued_at).num_seconds();
+        let rotation = match &certificate.rotation_error {
+            Some(error) => RotationHealth::Failing(error.clone()),
+            None if elapsed as f64 / lifetime as f64 >= thresholds.rotation_overdue_fraction => RotationHealth::Overdue,
+            None => RotationHealth::Healthy,
+        };
+        Self {
+            certificate,
+            seconds_to_expiry,
+            rotation,
+        }
+    }
+
+    // None for a healthy certificate that is outside the warning window
+    pub fn severity(&self, thresholds: &MtlsThresholds) -> Option<AlertSeverity> {
+        if self.seconds_to_expiry <= thresholds.critical_seconds {
+            Some(AlertSeverity::Critical)
+        } else if matches!(self.rotation, RotationHealth::Failing(_)) {
+            Some(AlertSeverity::Error)
+        } else if self.seconds_to_expiry <= thresholds.warning_seconds || self.rotation == RotationHealth::Overdue {
+            Some(AlertSeverity::Warning)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MtlsStatusReport {
+    pub namespace: String,
+    pub generated_at: DateTime<Utc>,
+    pub thresholds: MtlsThresholds,
+    pub workloads: Vec<WorkloadMtlsStatus>,
+    pub failures: Vec<CollectionFailure>,
+}
+
+impl MtlsStatusReport {
+    pub fn build(namespace: &str, collected: CollectedCertificates, thresholds: MtlsThresholds, now: DateTime<Utc>) -> Self {
+        let mut workloads: Vec<WorkloadMtlsStatus> = collected
+            .certificates
+            .into_iter()
+            .map(|cert| WorkloadMtlsStatus::evaluate(cert, &thresholds, now))
+            .collect();
+        workloads.sort_by_key(|w| w.seconds_to_expiry);
+        Self {
+            namespace: namespace.to_string(),
+            generated_at: now,
+            thresholds,
+            workloads,
+            failures: collected.failures,
+        }
+    }
+
+    pub fn flagged(&self) -> Vec<&WorkloadMtlsStatus> {
+        self.workloads
+            .iter()
+            .filter(|w| w.severity(&self.thresholds).is_some())
+            .collect()
+    }
+
+    // One firing alert per flagged workload; `value` is the hours left on the certificate
+    pub fn to_alert_events(&self) -> Vec<AlertEvent> {
+        let flagged = self.workloads.iter().filter_map(|status| {
+            let severity = status.severity(&self.thresholds)?;
+            let cert = &status.certificate;
+            let mut metadata = HashMap::new();
+            metadata.insert("namespace".to_string(), cert.namespace.clone());
+            metadata.insert("workload".to_string(), cert.workload.clone());
+            metadata.insert("expires_at".to_string(), cert.expires_at.to_rfc3339());
+            if let Some(issuer) = &cert.issuer {
+                metadata.insert("issuer".to_string(), issuer.clone());
+            }
+            let message = match &status.rotation {
+                RotationHealth::Failing(error) => {
+                    format!("mTLS certificate rotation failing for {}/{}: {}", cert.namespace, cert.workload, error)
+                }
+                _ if status.seconds_to_expiry <= 0 => {
+                    format!("mTLS certificate for {}/{} has expired", cert.namespace, cert.workload)
+                }
+                RotationHealth::Overdue => format!(
+                    "mTLS certificate for {}/{} has not rotated and expires at {}",
+                    cert.namespace, cert.workload, cert.expires_at
+                ),
+                RotationHealth::Healthy => format!(
+                    "mTLS certificate for {}/{} expires at {}",
+                    cert.namespace, cert.workload, cert.expires_at
+                ),
+            };
+            Some(self.alert(severity, message, status.seconds_to_expiry as f64 / 3600.0, metadata))
+        });
+        let unreadable = self.failures.iter().map(|failure| {
+            let mut metadata = HashMap::new();
+            metadata.insert("namespace".to_string(), self.namespace.clone());
+            metadata.insert("workload".to_string(), failure.workload.clone());
+            let message = format!(
+                "Could not read mTLS certificate for {}/{}: {}",
+                self.namespace, failure.workload, failure.error
+            );
+            self.alert(AlertSeverity::Warning, message, 0.0, metadata)
+        });
+        flagged.chain(unreadable).collect()
+    }
+
+    fn alert(&self, severity: AlertSeverity, message: String, value: f64, metadata: HashMap<String, String>) -> AlertEvent {
+        AlertEvent {
+            id: uuid::Uuid::new_v4().to_string(),
+            rule_id: MTLS_ALERT_RULE_ID.to_string(),
+            severity,
+            state: AlertState::Firing,
+            message,
+            value,
+            timestamp: self.generated_at,
+            resolved_at: None,
+            metadata,
+            actions: Vec::new(),
+        }
+    }
+}
+
+fn parse_error(source: &str, reason: impl std::fmt::Display) -> ComputeError {
+    ComputeError::Provider(format!("Unparseable {} output: {}", source, reason))
+}
+
+// `istioctl proxy-config secret <pod>.<namespace>`: a table whose columns are separated by
+// two or more spaces (TYPE values such as "Cert Chain" contain single spaces)
+pub fn parse_istio_secrets(workload: &str, namespace: &str, output: &str) -> ComputeResult<WorkloadCertificate> {
+    let split = |line: &str| -> Vec<String> {
+        line.split("  ").map(str::trim).filter(|c| !c.is_empty()).map(str::to_string).collect()
+    };
+    let mut lines = output.lines().filter(|l| !l.trim().is_empty());
+    let header = lines
+        .find(|l| l.trim_start().starts_with("RESOURCE NAME"))
+        .ok_or_else(|| parse_error("istioctl", "no RESOURCE NAME header"))?;
+    let columns = split(header);
+    let column = |name: &str| {
+        columns
+            .iter()
+            .position(|c| c == name)
+            .ok_or_else(|| parse_error("istioctl", format!("missing {} column", name)))
+    };
+    let (name, status, valid, serial, not_after, not_before) = (
+        column("RESOURCE NAME")?,
+        column("STATUS")?,
+        column("VALID CERT")?,
+        column("SERIAL NUMBER")?,
+        column("NOT AFTER")?,
+        column("NOT BEFORE")?,
+    );
+    let rows: Vec<Vec<String>> = lines.map(split).filter(|row| row.len() == columns.len()).collect();
+
+    // During rotation the new chain shows up as WARMING next to the ACTIVE one
+    let defaults: Vec<&Vec<String>> = rows.iter().filter(|row| row[name] == "default").collect();
+    let cert = defaults
+        .iter()
+        .find(|row| row[status] == "ACTIVE")
+        .or_else(|| defaults.first())
+        .ok_or_else(|| parse_error("istioctl", format!("no workload certificate for {}", workload)))?;
+    let root = rows.iter().find(|row| row[name] == "ROOTCA");
+    let time = |value: &str| {
+        DateTime::parse_from_rfc3339(value)
+            .map(|t| t.with_timezone(&Utc))
+            .map_err(|e| parse_error("istioctl", format!("bad timestamp {}: {}", value, e)))
+    };
+
+    let rotation_error = if cert[status] != "ACTIVE" {
+        Some(format!("workload certificate is {}", cert[status]))
+    } else if cert[valid] != "true" {
+        Some("proxy reports the workload certificate chain as invalid".to_string())
+    } else {
+        None
+    };
+    Ok(WorkloadCertificate {
+        workload: workload.to_string(),
+        namespace: namespace.to_string(),
+        serial_number: Some(cert[serial].clone()),
+        // The table carries no issuer name; the trust root's serial identifies it
+        issuer: root.map(|r| format!("ROOTCA {}", r[serial])),
+        issued_at: time(&cert[not_before])?,
+        expires_at: time(&cert[not_after])?,
+        rotation_error,
+    })
+}
+
+// `linkerd identity -n <namespace> <pod>`: the proxy certificate in `openssl x509 -text` form
+pub fn parse_linkerd_identity(workload: &str, namespace: &str, output: &str) -> ComputeResult<WorkloadCertificate> {
+    let lines: Vec<&str> = output.lines().map(str::trim).collect();
+    let field = |prefix: &str| {
+        lines.iter().enumerate().find_map(|(i, line)| {
+            let value = line.strip_prefix(prefix)?.trim();
+            // Long serials are printed as hex on the following line
+            Some(if value.is_empty() { lines.get(i + 1).copied().unwrap_or_default() } else { value })
+        })
+    };
+    let time = |prefix: &str| {
+        let value = field(prefix).ok_or_else(|| parse_error("linkerd", format!("missing {}", prefix.trim_end_matches(':'))))?;
+        let normalized = value.split_whitespace().collect::<Vec<_>>().join(" ");
+        NaiveDateTime::parse_from_str(&normalized, "%b %d %H:%M:%S %Y GMT")
+            .map(|t| t.and_utc())
+            .map_err(|e| parse_error("linkerd", format!("bad timestamp {}: {}", value, e)))
+    };
+    if !lines.contains(&"Certificate:") {
+        return Err(parse_error("linkerd", format!("no certificate for {}", workload)));
+    }
+
+    Ok(WorkloadCertificate {
+        workload: workload.to_string(),
+        namespace: namespace.to_string(),
+        serial_number: field("Serial Number:").and_then(|s| s.split_whitespace().next()).map(str::to_string),
+        issuer: field("Issuer:").map(str::to_string),
+        issued_at: time("Not Before:")?,
+        expires_at: time("Not After :")?,
+        rotation_error: None,
+    })
+}
+
+// Shells out to kubectl and the mesh CLI for every meshed pod in the namespace
+pub struct CliCertificateCollector {
+    mesh: MeshType,
+}
+
+impl CliCertificateCollector {
+    pub fn new(mesh: MeshType) -> Self {
+        Self { mesh }
+    }
+
+    async fn run(program: &str, args: &[&str]) -> ComputeResult<String> {
+        let output = tokio::process::Command::new(program)
+            .args(args)
+            .output()
+            .await
+            .map_err(|e| ComputeError::Provider(format!("Failed to run {}: {}", program, e)))?;
+        if !output.status.success() {
+            return Err(ComputeError::Provider(format!(
+                "{} {} failed: {}",
+                program,
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+#[async_trait]
+impl CertificateCollector for CliCertificateCollector {
+    async fn collect(&self, namespace: &str) -> ComputeResult<CollectedCertificates> {
+        let selector = match self.mesh {
+            MeshType::Istio => "security.istio.io/tlsMode=istio",
+            MeshType::Linkerd => "linkerd.io/control-plane-ns",
+            MeshType::ConsulConnect => {
+                return Err(ComputeError::Validation("Consul Connect certificate status is not supported".into()))
+            }
+        };
+        let pods = Self::run(
+            "kubectl",
+            &["get", "pods", "-n", namespace, "-l", selector, "-o", "jsonpath={.items[*].metadata.name}"],
+        )
+        .await?;
+
+        let mut collected = CollectedCertificates::default();
+        for pod in pods.split_whitespace() {
+            let result = match self.mesh {
+                MeshType::Istio => {
+                    let target = format!("{}.{}", pod, namespace);
+                    match Self::run("istioctl", &["proxy-config", "secret", &target]).await {
+                        Ok(output) => parse_istio_secrets(pod, namespace, &output),
+                        Err(e) => Err(e),
+                    }
+                }
+                _ => match Self::run("linkerd", &["identity", "-n", namespace, pod]).await {
+                    Ok(output) => parse_linkerd_identity(pod, namespace, &output),
+                    Err(e) => Err(e),
+                },
+            };
+            match result {
+                Ok(cert) => collected.certificates.push(cert),
+                Err(e) => {
+                    warn!("Could not read mTLS certificate for {}/{}: {}", namespace, pod, e);
+                    collected.failures.push(CollectionFailure {
+                        workload: pod.to_string(),
+                        error: e.to_string(),
+                    });
+                }
+            }
+        }
+        Ok(collected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    const ISTIOCTL_SECRETS: &str = "\
+RESOURCE NAME     TYPE           STATUS      VALID CERT     SERIAL NUMBER                               NOT AFTER                NOT BEFORE
+default           Cert Chain     ACTIVE      true           26729164703556645285213068813335299044      2024-01-12T10:13:00Z     2024-01-11T10:11:00Z
+default           Cert Chain     WARMING     true           31542705176331434308837254858819834622      2024-01-12T22:13:00Z     2024-01-11T22:11:00Z
+ROOTCA            CA             ACTIVE      true           289461823806618439112225337283596871132     2034-01-08T10:06:35Z     2024-01-11T10:06:35Z
+";
+
+    const LINKERD_IDENTITY: &str = "\
+POD web-5f86686c4d-58p7k (1 of 1)
+
+Certificate:
+    Data:
+        Version: 3 (0x2)
+        Serial Number: 1 (0x1)
+        Signature Algorithm: ecdsa-with-SHA256
+        Issuer: CN = identity.linkerd.cluster.local
+        Validity
+            Not Before: Apr  5 17:23:39 2021 GMT
+            Not After : Apr  6 17:24:19 2021 GMT
+        Subject: CN = web.emojivoto.serviceaccount.identity.linkerd.cluster.local
+";
+
+    fn at(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_parse_istio_secrets() {
+        let cert = parse_istio_secrets("reviews-v1-7f9c", "bookinfo", ISTIOCTL_SECRETS).unwrap();
+        assert_eq!(cert.serial_number.as_deref(), Some("26729164703556645285213068813335299044"));
+        assert_eq!(cert.issued_at, at("2024-01-11T10:11:00Z"));
+        assert_eq!(cert.expires_at, at("2024-01-12T10:13:00Z"));
+        assert_eq!(cert.issuer.as_deref(), Some("ROOTCA 289461823806618439112225337283596871132"));
+        assert!(cert.rotation_error.is_none());
+
+        let stuck = ISTIOCTL_SECRETS.replacen("ACTIVE      true", "WARMING     false", 1);
+        let stuck = stuck.replacen("WARMING     true", "WARMING     false", 1);
+        let cert = parse_istio_secrets("reviews-v1-7f9c", "bookinfo", &stuck).unwrap();
+        assert_eq!(cert.rotation_error.as_deref(), Some("workload certificate is WARMING"));
+        assert!(parse_istio_secrets("reviews-v1-7f9c", "bookinfo", "no secrets found").is_err());
+    }
+
+    #[test]
+    fn test_parse_linkerd_identity() {
+        let cert = parse_linkerd_identity("web-5f86686c4d-58p7k", "emojivoto", LINKERD_IDENTITY).unwrap();
+        assert_eq!(cert.serial_number.as_deref(), Some("1"));
+        assert_eq!(cert.issuer.as_deref(), Some("CN = identity.linkerd.cluster.local"));
+        assert_eq!(cert.issued_at, at("2021-04-05T17:23:39Z"));
+        assert_eq!(cert.expires_at, at("2021-04-06T17:24:19Z"));
+        assert!(parse_linkerd_identity("web", "emojivoto", "POD web (1 of 1)\n").is_err());
+    }
+
+    #[test]
+    fn test_alert_thresholds() {
+        let now = at("2024-01-11T12:00:00Z");
+        let cert = |workload: &str, issued_hours_ago: i64, expires_in_hours: i64, error: Option<&str>| WorkloadCertificate {
+            workload: workload.to_string(),
+            namespace: "bookinfo".to_string(),
+            serial_number: None,
+            issuer: None,
+            issued_at: now - Duration::hours(issued_hours_ago),
+            expires_at: now + Duration::hours(expires_in_hours),
+            rotation_error: error.map(str::to_string),
+        };
+        let collected = CollectedCertificates {
+            certificates: vec![
+                cert("fresh", 2, 22, None),
+                cert("expiring", 19, 5, None),
+                cert("expired", 25, -1, None),
+                cert("long-lived", 20, 28, None),
+                cert("failing", 1, 23, Some("workload certificate is WARMING")),
+            ],
+            failures: vec![CollectionFailure {
+                workload: "no-proxy".to_string(),
+                error: "istioctl failed".to_string(),
+            }],
+        };
+
+        let report = MtlsStatusReport::build("bookinfo", collected, MtlsThresholds::default(), now);
+        assert_eq!(report.workloads[0].certificate.workload, "expired");
+        assert_eq!(report.flagged().len(), 3);
+
+        let alerts = report.to_alert_events();
+        let severity = |workload: &str| {
+            alerts
+                .iter()
+                .find(|a| a.metadata.get("workload").map(String::as_str) == Some(workload))
+                .map(|a| format!("{:?}", a.severity))
+        };
+        assert_eq!(severity("fresh"), None);
+        assert_eq!(severity("expiring").as_deref(), Some("Warning"));
+        assert_eq!(severity("expired").as_deref(), Some("Critical"));
+        assert_eq!(severity("long-lived"), None);
+        assert_eq!(severity("failing").as_deref(), Some("Error"));
+        assert_eq!(severity("no-proxy").as_deref(), Some("Warning"));
+        assert!(alerts.iter().all(|a| a.rule_id == MTLS_ALERT_RULE_ID));
+
+        let overdue = MtlsStatusReport::build(
+            "bookinfo",
+            CollectedCertificates {
+                certificates: vec![cert("stuck", 40, 8, None)],
+                failures: vec![],
+            },
+            MtlsThresholds::default(),
+            now,
+        );
+        assert_eq!(overdue.workloads[0].rotation, RotationHealth::Overdue);
+        assert!(matches!(overdue.to_alert_events()[0].severity, AlertSeverity::Warning));
+    }
+}
@@ -0,0 +1,741 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use tracing::{info, warn};
+
+use crate::error::{ComputeError, ComputeResult};
+use super::ResourceRequirements;
+
+pub const MANAGED_BY_LABEL: &str = "app.kubernetes.io/managed-by";
+pub const MANAGED_BY: &str = "sirsi-mesh-onboarding";
+pub const INJECTION_LABEL: &str = "istio-injection";
+
+const PROXY_CONTAINER: &str = "istio-proxy";
+const SECURITY_API: &str = "security.istio.io/v1beta1";
+const TELEMETRY_API: &str = "telemetry.istio.io/v1alpha1";
+// Kinds onboarding creates; offboarding removes whatever of these carries the managed-by label
+const MANAGED_KINDS: [(&str, &str); 3] = [
+    (SECURITY_API, "PeerAuthentication"),
+    (SECURITY_API, "AuthorizationPolicy"),
+    (TELEMETRY_API, "Telemetry"),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct ResourceKey {
+    pub api_version: String,
+    pub kind: String,
+    pub namespace: Option<String>,
+    pub name: String,
+}
+
+impl ResourceKey {
+    pub fn new(api_version: &str, kind: &str, namespace: Option<&str>, name: &str) -> Self {
+        Self {
+            api_version: api_version.to_string(),
+            kind: kind.to_string(),
+            namespace: namespace.map(str::to_string),
+            name: name.to_string(),
+        }
+    }
+
+    pub fn namespace(name: &str) -> Self {
+        Self::new("v1", "Namespace", None, name)
+    }
+
+    pub fn of(manifest: &Value) -> ComputeResult<Self> {
+        let field = |path: &[&str]| {
+            path.iter()
+                .try_fold(manifest, |value, key| value.get(key))
+                .and_then(Value::as_str)
+        };
+        match (field(&["apiVersion"]), field(&["kind"]), field(&["metadata", "name"])) {
+            (Some(api_version), Some(kind), Some(name)) => {
+                Ok(Self::new(api_version, kind, field(&["metadata", "namespace"]), name))
+            }
+            _ => Err(ComputeError::Validation("Manifest needs apiVersion, kind and metadata.name".into())),
+        }
+    }
+}
+
+impl std::fmt::Display for ResourceKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.kind, self.name)
+    }
+}
+
+// The slice of the Kubernetes API onboarding needs, over unstructured manifests
+#[async_trait]
+pub trait MeshClusterClient: Send + Sync {
+    async fn get(&self, key: &ResourceKey) -> ComputeResult<Option<Value>>;
+    async fn list(&self, namespace: &str, api_version: &str, kind: &str, label_selector: Option<&str>) -> ComputeResult<Vec<Value>>;
+    async fn apply(&self, manifest: Value) -> ComputeResult<()>;
+    // Returns false if the resource did not exist
+    async fn delete(&self, key: &ResourceKey) -> ComputeResult<bool>;
+    // JSON merge patch (RFC 7386): null values remove keys
+    async fn patch(&self, key: &ResourceKey, patch: Value) -> ComputeResult<()>;
+    // Rolls the deployment and returns the name of a fresh, ready pod
+    async fn restart_deployment(&self, namespace: &str, name: &str) -> ComputeResult<String>;
+    // Whether a request from `pod` to `target` reached it over mTLS
+    async fn probe_mtls(&self, namespace: &str, pod: &str, target: &str) -> ComputeResult<bool>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PeerAuthenticationMode {
+    Strict,
+    Permissive,
+}
+
+// A client allowed through the namespace's deny-all policy
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NamespaceDependency {
+    pub name: String,
+    pub namespace: String,
+    pub service_account: Option<String>,
+    pub ports: Vec<u16>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TelemetryDefaults {
+    pub tracing_sampling_percentage: f64,
+    pub access_logging: bool,
+}
+
+// `deployment`'s main container needs curl (e.g. Istio's sleep sample); `probe_service` is an
+// httpbin-style host:port that echoes request headers back
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CanaryCheck {
+    pub deployment: String,
+    pub probe_service: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OnboardingOptions {
+    pub mtls_mode: PeerAuthenticationMode,
+    pub dependencies: Vec<NamespaceDependency>,
+    pub sidecar_resources: ResourceRequirements,
+    pub telemetry: TelemetryDefaults,
+    pub canary: Option<CanaryCheck>,
+}
+
+impl OnboardingOptions {
+    pub fn new(mtls_mode: PeerAuthenticationMode) -> Self {
+        Self {
+            mtls_mode,
+            dependencies: Vec::new(),
+            sidecar_resources: ResourceRequirements {
+                cpu_request: "100m".to_string(),
+                memory_request: "128Mi".to_string(),
+                cpu_limit: "2000m".to_string(),
+                memory_limit: "1Gi".to_string(),
+            },
+            telemetry: TelemetryDefaults {
+                tracing_sampling_percentage: 1.0,
+                access_logging: true,
+            },
+            canary: None,
+        }
+    }
+
+    pub fn with_dependency(mut self, dependency: NamespaceDependency) -> Self {
+        self.dependencies.push(dependency);
+        self
+    }
+
+    pub fn with_canary(mut self, canary: CanaryCheck) -> Self {
+        self.canary = Some(canary);
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum StepOutcome {
+    Created,
+    Updated,
+    Unchanged,
+    Deleted,
+    Absent,
+    Passed,
+    Failed(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OnboardingStep {
+    pub name: String,
+    pub outcome: StepOutcome,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OnboardingReport {
+    pub namespace: String,
+    pub steps: Vec<OnboardingStep>,
+    pub succeeded: bool,
+}
+
+impl OnboardingReport {
+    fn new(namespace: &str) -> Self {
+        Self {
+            namespace: namespace.to_string(),
+            steps: Vec::new(),
+            succeeded: true,
+        }
+    }
+
+    // Returns false once a step has failed; later steps are not attempted
+    fn record(&mut self, name: impl Into<String>, outcome: ComputeResult<StepOutcome>) -> bool {
+        let name = name.into();
+        let outcome = outcome.unwrap_or_else(|e| StepOutcome::Failed(e.to_string()));
+        if let StepOutcome::Failed(reason) = &outcome {
+            warn!("Mesh onboarding step {} failed for {}: {}", name, self.namespace, reason);
+            self.succeeded = false;
+        }
+        self.steps.push(OnboardingStep { name, outcome });
+        self.succeeded
+    }
+
+    pub fn step(&self, name: &str) -> Option<&StepOutcome> {
+        self.steps.iter().find(|s| s.name == name).map(|s| &s.outcome)
+    }
+}
+
+fn managed_metadata(name: &str, namespace: &str) -> Value {
+    json!({
+        "name": name,
+        "namespace": namespace,
+        "labels": { MANAGED_BY_LABEL: MANAGED_BY },
+    })
+}
+
+fn peer_authentication(namespace: &str, mode: PeerAuthenticationMode) -> Value {
+    let mode = match mode {
+        PeerAuthenticationMode::Strict => "STRICT",
+        PeerAuthenticationMode::Permissive => "PERMISSIVE",
+    };
+    json!({
+        "apiVersion": SECURITY_API,
+        "kind": "PeerAuthentication",
+        "metadata": managed_metadata("default", namespace),
+        "spec": { "mtls": { "mode": mode } },
+    })
+}
+
+// An empty spec matches nothing, which Istio treats as deny-all for the namespace
+fn deny_all(namespace: &str) -> Value {
+    json!({
+        "apiVersion": SECURITY_API,
+        "kind": "AuthorizationPolicy",
+        "metadata": managed_metadata("deny-all", namespace),
+        "spec": {},
+    })
+}
+
+fn allow_dependency(namespace: &str, dependency: &NamespaceDependency) -> Value {
+    let source = match &dependency.service_account {
+        Some(account) => json!({
+            "principals": [format!("cluster.local/ns/{}/sa/{}", dependency.namespace, account)],
+        }),
+        None => json!({ "namespaces": [dependency.namespace] }),
+    };
+    let mut rule = json!({ "from": [{ "source": source }] });
+    if !dependency.ports.is_empty() {
+        let ports: Vec<String> = dependency.ports.iter().map(u16::to_string).collect();
+        rule["to"] = json!([{ "operation": { "ports": ports } }]);
+    }
+    json!({
+        "apiVersion": SECURITY_API,
+        "kind": "AuthorizationPolicy",
+        "metadata": managed_metadata(&format!("allow-{}", dependency.name), namespace),
+        "spec": { "action": "ALLOW", "rules": [rule] },
+    })
+}
+
+fn telemetry(namespace: &str, defaults: &TelemetryDefaults) -> Value {
+    json!({
+        "apiVersion": TELEMETRY_API,
+        "kind": "Telemetry",
+        "metadata": managed_metadata("default", namespace),
+        "spec": {
+            "tracing": [{ "randomSamplingPercentage": defaults.tracing_sampling_percentage }],
+            "accessLogging": [{ "providers": [{ "name": "envoy" }], "disabled": !defaults.access_logging }],
+        },
+    })
+}
+
+pub fn desired_resources(namespace: &str, options: &OnboardingOptions) -> Vec<Value> {
+    let mut resources = vec![peer_authentication(namespace, options.mtls_mode), deny_all(namespace)];
+    resources.extend(options.dependencies.iter().map(|d| allow_dependency(namespace, d)));
+    resources.push(telemetry(namespace, &options.telemetry));
+    resources
+}
+
+// Istio reads proxy resources from pod annotations, so they go on each deployment's pod template
+const SIDECAR_ANNOTATIONS: [&str; 4] = [
+    "sidecar.istio.io/proxyCPU",
+    "sidecar.istio.io/proxyMemory",
+    "sidecar.istio.io/proxyCPULimit",
+    "sidecar.istio.io/proxyMemoryLimit",
+];
+
+fn sidecar_annotations(resources: &ResourceRequirements) -> Map<String, Value> {
+    let values = [
+        &resources.cpu_request,
+        &resources.memory_request,
+        &resources.cpu_limit,
+        &resources.memory_limit,
+    ];
+    SIDECAR_ANNOTATIONS
+        .iter()
+        .zip(values)
+        .map(|(key, value)| (key.to_string(), Value::String(value.clone())))
+        .collect()
+}
+
+fn name_of(manifest: &Value) -> &str {
+    manifest["metadata"]["name"].as_str().unwrap_or_default()
+}
+
+fn container_names(pod: &Value) -> Vec<&str> {
+    // Native sidecars (Kubernetes 1.28+) are injected as restartable init containers
+    ["containers", "initContainers"]
+        .iter()
+        .filter_map(|field| pod["spec"][field].as_array())
+        .flatten()
+        .filter_map(|c| c["name"].as_str())
+        .collect()
+}
+
+pub struct NamespaceOnboarder {
+    cluster: Arc<dyn MeshClusterClient>,
+}
+
+impl NamespaceOnboarder {
+    pub fn new(cluster: Arc<dyn MeshClusterClient>) -> Self {
+        Self { cluster }
+    }
+
+    async fn namespace_labels(&self, namespace: &str) -> ComputeResult<Map<String, Value>> {
+        let ns = self
+            .cluster
+            .get(&ResourceKey::namespace(namespace))
+            .await?
+            .ok_or_else(|| ComputeError::NotFound(format!("Namespace {} not found", namespace)))?;
+        Ok(ns["metadata"]["labels"].as_object().cloned().unwrap_or_default())
+    }
+
+    async fn ensure(&self, manifest: Value) -> ComputeResult<StepOutcome> {
+        let key = ResourceKey::of(&manifest)?;
+        let outcome = match self.cluster.get(&key).await? {
+            None => StepOutcome::Created,
+            Some(existing)
+                if existing["spec"] == manifest["spec"]
+                    && existing["metadata"]["labels"][MANAGED_BY_LABEL] == MANAGED_BY =>
+            {
+                return Ok(StepOutcome::Unchanged)
+            }
+            Some(_) => StepOutcome::Updated,
+        };
+        self.cluster.apply(manifest).await?;
+        Ok(outcome)
+    }
+
+    async fn patch_template_annotations(&self, deployment: &Value, annotations: Map<String, Value>) -> ComputeResult<StepOutcome> {
+        let current = &deployment["spec"]["template"]["metadata"]["annotations"];
+        if annotations.iter().all(|(k, v)| current.get(k).unwrap_or(&Value::Null) == v) {
+            return Ok(StepOutcome::Unchanged);
+        }
+        let key = ResourceKey::of(deployment)?;
+        let patch = json!({ "spec": { "template": { "metadata": { "annotations": annotations } } } });
+        self.cluster.patch(&key, patch).await?;
+        Ok(StepOutcome::Updated)
+    }
+
+    async fn verify_canary(&self, namespace: &str, canary: &CanaryCheck, report: &mut OnboardingReport) {
+        let pod = match self.cluster.restart_deployment(namespace, &canary.deployment).await {
+            Ok(pod) => pod,
+            Err(e) => {
+                report.record("canary sidecar", Err(e));
+                return;
+            }
+        };
+        let sidecar = match self.cluster.get(&ResourceKey::new("v1", "Pod", Some(namespace), &pod)).await {
+            Ok(Some(manifest)) if container_names(&manifest).contains(&PROXY_CONTAINER) => StepOutcome::Passed,
+            Ok(Some(_)) => StepOutcome::Failed(format!("Pod {} started without an {} sidecar", pod, PROXY_CONTAINER)),
+            Ok(None) => StepOutcome::Failed(format!("Pod {} disappeared after restart", pod)),
+            Err(e) => StepOutcome::Failed(e.to_string()),
+        };
+        if !report.record("canary sidecar", Ok(sidecar)) {
+            return;
+        }
+        let handshake = match self.cluster.probe_mtls(namespace, &pod, &canary.probe_service).await {
+            Ok(true) => Ok(StepOutcome::Passed),
+            Ok(false) => Ok(StepOutcome::Failed(format!(
+                "Request from {} to {} was not mutually authenticated",
+                pod, canary.probe_service
+            ))),
+            Err(e) => Err(e),
+        };
+        report.record("canary mTLS", handshake);
+    }
+
+    // Safe to re-run: resources already in the desired state are reported Unchanged
+    pub async fn onboard(&self, namespace: &str, options: &OnboardingOptions) -> ComputeResult<OnboardingReport> {
+        let labels = self.namespace_labels(namespace).await?;
+        let mut report = OnboardingReport::new(namespace);
+
+        let injection = if labels.get(INJECTION_LABEL).and_then(Value::as_str) == Some("enabled") {
+            Ok(StepOutcome::Unchanged)
+        } else {
+            let patch = json!({ "metadata": { "labels": { INJECTION_LABEL: "enabled" } } });
+            self.cluster
+                .patch(&ResourceKey::namespace(namespace), patch)
+                .await
+                .map(|_| StepOutcome::Updated)
+        };
+        if !report.record("Namespace injection label", injection) {
+            return Ok(report);
+        }
+
+        let desired = desired_resources(namespace, options);
+        for manifest in &desired {
+            let name = ResourceKey::of(manifest)?.to_string();
+            if !report.record(name, self.ensure(manifest.clone()).await) {
+                return Ok(report);
+            }
+        }
+
+        // Allow policies for dependencies that have since been dropped from the options
+        let wanted: HashSet<&str> = desired
+            .iter()
+            .filter(|m| m["kind"] == "AuthorizationPolicy")
+            .map(name_of)
+            .collect();
+        let selector = format!("{}={}", MANAGED_BY_LABEL, MANAGED_BY);
+        let existing = self
+            .cluster
+            .list(namespace, SECURITY_API, "AuthorizationPolicy", Some(&selector))
+            .await?;
+        for stale in existing.iter().filter(|p| !wanted.contains(name_of(p))) {
+            let key = ResourceKey::of(stale)?;
+            let outcome = self.cluster.delete(&key).await.map(|_| StepOutcome::Deleted);
+            if !report.record(key.to_string(), outcome) {
+                return Ok(report);
+            }
+        }
+
+        let annotations = sidecar_annotations(&options.sidecar_resources);
+        for deployment in self.cluster.list(namespace, "apps/v1", "Deployment", None).await? {
+            let name = format!("Deployment/{} sidecar resources", name_of(&deployment));
+            let outcome = self.patch_template_annotations(&deployment, annotations.clone()).await;
+            if !report.record(name, outcome) {
+                return Ok(report);
+            }
+        }
+
+        if let Some(canary) = &options.canary {
+            self.verify_canary(namespace, canary, &mut report).await;
+        }
+        info!(
+            "Onboarded namespace {} into the mesh: {}",
+            namespace,
+            if report.succeeded { "verified" } else { "failed" }
+        );
+        Ok(report)
+    }
+
+    // Removes everything `onboard` added. Running pods keep their sidecars until they restart.
+    pub async fn offboard(&self, namespace: &str) -> ComputeResult<OnboardingReport> {
+        let labels = self.namespace_labels(namespace).await?;
+        let mut report = OnboardingReport::new(namespace);
+        let selector = format!("{}={}", MANAGED_BY_LABEL, MANAGED_BY);
+
+        for (api_version, kind) in MANAGED_KINDS {
+            let managed = self.cluster.list(namespace, api_version, kind, Some(&selector)).await?;
+            if managed.is_empty() {
+                report.record(kind, Ok(StepOutcome::Absent));
+                continue;
+            }
+            for manifest in managed {
+                let key = ResourceKey::of(&manifest)?;
+                let outcome = self.cluster.delete(&key).await.map(|existed| {
+                    if existed { StepOutcome::Deleted } else { StepOutcome::Absent }
+                });
+                if !report.record(key.to_string(), outcome) {
+                    return Ok(report);
+                }
+            }
+        }
+
+        let removals: Map<String, Value> = SIDECAR_ANNOTATIONS.iter().map(|k| (k.to_string(), Value::Null)).collect();
+        for deployment in self.cluster.list(namespace, "apps/v1", "Deployment", None).await? {
+            let name = format!("Deployment/{} sidecar resources", name_of(&deployment));
+            let outcome = self.patch_template_annotations(&deployment, removals.clone()).await;
+            if !report.record(name, outcome) {
+                return Ok(report);
+            }
+        }
+
+        let injection = if labels.contains_key(INJECTION_LABEL) {
+            let patch = json!({ "metadata": { "labels": { INJECTION_LABEL: null } } });
+            self.cluster
+                .patch(&ResourceKey::namespace(namespace), patch)
+                .await
+                .map(|_| StepOutcome::Updated)
+        } else {
+            Ok(StepOutcome::Unchanged)
+        };
+        report.record("Namespace injection label", injection);
+        info!("Offboarded namespace {} from the mesh", namespace);
+        Ok(report)
+    }
+}
+
+// RFC 7386 merge patch, shared by client implementations that patch locally
+pub fn merge_patch(target: &mut Value, patch: &Value) {
+    let Some(patch) = patch.as_object() else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = json!({});
+    }
+    let object = target.as_object_mut().expect("target was just made an object");
+    for (key, value) in patch {
+        if value.is_null() {
+            object.remove(key);
+        } else {
+            merge_patch(object.entry(key.clone()).or_insert(Value::Null), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Mutex;
+
+    // In-memory API server: objects by key, plus a log of mutating calls
+    #[derive(Default)]
+    struct FakeCluster {
+        objects: Mutex<BTreeMap<ResourceKey, Value>>,
+        mutations: Mutex<Vec<String>>,
+        injector_down: AtomicBool,
+    }
+
+    impl FakeCluster {
+        fn with_namespace(namespace: &str, deployments: &[&str]) -> Arc<Self> {
+            let cluster = Arc::new(Self::default());
+            let mut objects = cluster.objects.lock().unwrap();
+            objects.insert(
+                ResourceKey::namespace(namespace),
+                json!({ "apiVersion": "v1", "kind": "Namespace", "metadata": { "name": namespace, "labels": { "team": "payments" } } }),
+            );
+            for name in deployments {
+                let deployment = json!({
+                    "apiVersion": "apps/v1",
+                    "kind": "Deployment",
+                    "metadata": { "name": name, "namespace": namespace },
+                    "spec": { "template": { "metadata": { "annotations": { "prometheus.io/scrape": "true" } } } },
+                });
+                objects.insert(ResourceKey::of(&deployment).unwrap(), deployment);
+            }
+            drop(objects);
+            cluster
+        }
+
+        fn keys(&self) -> Vec<String> {
+            self.objects.lock().unwrap().keys().map(|k| k.to_string()).collect()
+        }
+
+        fn object(&self, key: &ResourceKey) -> Value {
+            self.objects.lock().unwrap()[key].clone()
+        }
+
+        fn take_mutations(&self) -> Vec<String> {
+            std::mem::take(&mut *self.mutations.lock().unwrap())
+        }
+    }
+
+    #[async_trait]
+    impl MeshClusterClient for FakeCluster {
+        async fn get(&self, key: &ResourceKey) -> ComputeResult<Option<Value>> {
+            Ok(self.objects.lock().unwrap().get(key).cloned())
+        }
+
+        async fn list(&self, namespace: &str, api_version: &str, kind: &str, label_selector: Option<&str>) -> ComputeResult<Vec<Value>> {
+            let (label, value) = label_selector.and_then(|s| s.split_once('=')).unwrap_or_default();
+            Ok(self
+                .objects
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|(k, _)| k.api_version == api_version && k.kind == kind && k.namespace.as_deref() == Some(namespace))
+                .filter(|(_, v)| label.is_empty() || v["metadata"]["labels"][label] == value)
+                .map(|(_, v)| v.clone())
+                .collect())
+        }
+
+        async fn apply(&self, manifest: Value) -> ComputeResult<()> {
+            let key = ResourceKey::of(&manifest)?;
+            self.mutations.lock().unwrap().push(format!("apply {}", key));
+            self.objects.lock().unwrap().insert(key, manifest);
+            Ok(())
+        }
+
+        async fn delete(&self, key: &ResourceKey) -> ComputeResult<bool> {
+            self.mutations.lock().unwrap().push(format!("delete {}", key));
+            Ok(self.objects.lock().unwrap().remove(key).is_some())
+        }
+
+        async fn patch(&self, key: &ResourceKey, patch: Value) -> ComputeResult<()> {
+            self.mutations.lock().unwrap().push(format!("patch {}", key));
+            let mut objects = self.objects.lock().unwrap();
+            let object = objects.get_mut(key).ok_or_else(|| ComputeError::NotFound(key.to_string()))?;
+            merge_patch(object, &patch);
+            Ok(())
+        }
+
+        // The injector only adds a sidecar when the namespace is labelled
+        async fn restart_deployment(&self, namespace: &str, name: &str) -> ComputeResult<String> {
+            let injected = self.objects.lock().unwrap()[&ResourceKey::namespace(namespace)]["metadata"]["labels"]
+                [INJECTION_LABEL]
+                == "enabled"
+                && !self.injector_down.load(Ordering::SeqCst);
+            let pod_name = format!("{}-7d4b9", name);
+            let mut containers = vec![json!({ "name": "app" })];
+            if injected {
+                containers.push(json!({ "name": PROXY_CONTAINER }));
+            }
+            let pod = json!({
+                "apiVersion": "v1",
+                "kind": "Pod",
+                "metadata": { "name": pod_name, "namespace": namespace },
+                "spec": { "containers": containers },
+            });
+            self.objects.lock().unwrap().insert(ResourceKey::of(&pod)?, pod);
+            Ok(pod_name)
+        }
+
+        async fn probe_mtls(&self, namespace: &str, _pod: &str, _target: &str) -> ComputeResult<bool> {
+            let key = ResourceKey::new(SECURITY_API, "PeerAuthentication", Some(namespace), "default");
+            Ok(self.objects.lock().unwrap().contains_key(&key))
+        }
+    }
+
+    fn options() -> OnboardingOptions {
+        OnboardingOptions::new(PeerAuthenticationMode::Strict)
+            .with_dependency(NamespaceDependency {
+                name: "frontend".to_string(),
+                namespace: "web".to_string(),
+                service_account: Some("storefront".to_string()),
+                ports: vec![8080],
+            })
+            .with_dependency(NamespaceDependency {
+                name: "monitoring".to_string(),
+                namespace: "observability".to_string(),
+                service_account: None,
+                ports: vec![],
+            })
+            .with_canary(CanaryCheck {
+                deployment: "sleep".to_string(),
+                probe_service: "httpbin.payments:8000".to_string(),
+            })
+    }
+
+    #[tokio::test]
+    async fn test_onboard_creates_resources_and_is_idempotent() {
+        let cluster = FakeCluster::with_namespace("payments", &["api", "sleep"]);
+        let onboarder = NamespaceOnboarder::new(cluster.clone());
+
+        let report = onboarder.onboard("payments", &options()).await.unwrap();
+        assert!(report.succeeded, "{:?}", report.steps);
+        assert_eq!(report.step("canary mTLS"), Some(&StepOutcome::Passed));
+        assert_eq!(
+            cluster.take_mutations(),
+            vec![
+                "patch Namespace/payments",
+                "apply PeerAuthentication/default",
+                "apply AuthorizationPolicy/deny-all",
+                "apply AuthorizationPolicy/allow-frontend",
+                "apply AuthorizationPolicy/allow-monitoring",
+                "apply Telemetry/default",
+                "patch Deployment/api",
+                "patch Deployment/sleep",
+            ]
+        );
+        let peer = cluster.object(&ResourceKey::new(SECURITY_API, "PeerAuthentication", Some("payments"), "default"));
+        assert_eq!(peer["spec"]["mtls"]["mode"], "STRICT");
+        let allow = cluster.object(&ResourceKey::new(SECURITY_API, "AuthorizationPolicy", Some("payments"), "allow-frontend"));
+        assert_eq!(allow["spec"]["rules"][0]["from"][0]["source"]["principals"][0], "cluster.local/ns/web/sa/storefront");
+        assert_eq!(allow["spec"]["rules"][0]["to"][0]["operation"]["ports"][0], "8080");
+        let api = cluster.object(&ResourceKey::new("apps/v1", "Deployment", Some("payments"), "api"));
+        assert_eq!(api["spec"]["template"]["metadata"]["annotations"]["sidecar.istio.io/proxyCPU"], "100m");
+        assert_eq!(api["spec"]["template"]["metadata"]["annotations"]["prometheus.io/scrape"], "true");
+
+        let rerun = onboarder.onboard("payments", &options()).await.unwrap();
+        assert!(rerun.succeeded);
+        assert!(cluster.take_mutations().is_empty());
+        assert!(rerun
+            .steps
+            .iter()
+            .all(|s| matches!(s.outcome, StepOutcome::Unchanged | StepOutcome::Passed)));
+
+        // Dropping a dependency and relaxing mTLS prunes and updates in place
+        let mut relaxed = options();
+        relaxed.mtls_mode = PeerAuthenticationMode::Permissive;
+        relaxed.dependencies.truncate(1);
+        let report = onboarder.onboard("payments", &relaxed).await.unwrap();
+        assert_eq!(report.step("PeerAuthentication/default"), Some(&StepOutcome::Updated));
+        assert_eq!(report.step("AuthorizationPolicy/allow-monitoring"), Some(&StepOutcome::Deleted));
+    }
+
+    #[tokio::test]
+    async fn test_offboard_reverses_onboarding() {
+        let cluster = FakeCluster::with_namespace("payments", &["api", "sleep"]);
+        let before: Vec<Value> = cluster.objects.lock().unwrap().values().cloned().collect();
+        let onboarder = NamespaceOnboarder::new(cluster.clone());
+        onboarder.onboard("payments", &options()).await.unwrap();
+
+        let report = onboarder.offboard("payments").await.unwrap();
+        assert!(report.succeeded);
+        assert_eq!(report.step("Namespace injection label"), Some(&StepOutcome::Updated));
+        // Only the canary pod the verification created outlives offboarding
+        assert_eq!(cluster.keys(), vec!["Deployment/api", "Deployment/sleep", "Namespace/payments", "Pod/sleep-7d4b9"]);
+        let after: Vec<Value> = cluster
+            .objects
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(k, _)| k.kind != "Pod")
+            .map(|(_, v)| v.clone())
+            .collect();
+        assert_eq!(after, before);
+
+        cluster.take_mutations();
+        let again = onboarder.offboard("payments").await.unwrap();
+        assert!(cluster.take_mutations().is_empty());
+        assert_eq!(again.step("PeerAuthentication"), Some(&StepOutcome::Absent));
+    }
+
+    #[tokio::test]
+    async fn test_canary_without_sidecar_fails_verification() {
+        let cluster = FakeCluster::with_namespace("payments", &["sleep"]);
+        cluster.injector_down.store(true, Ordering::SeqCst);
+        let onboarder = NamespaceOnboarder::new(cluster.clone());
+
+        let report = onboarder.onboard("payments", &options()).await.unwrap();
+        assert!(!report.succeeded);
+        assert!(matches!(report.step("canary sidecar"), Some(StepOutcome::Failed(_))));
+        assert_eq!(report.step("canary mTLS"), None);
+        assert_eq!(report.step("Telemetry/default"), Some(&StepOutcome::Created));
+
+        let missing = FakeCluster::with_namespace("ledger", &[]);
+        assert!(matches!(
+            NamespaceOnboarder::new(missing).onboard("payments", &options()).await,
+            Err(ComputeError::NotFound(_))
+        ));
+    }
+}
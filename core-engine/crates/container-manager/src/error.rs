@@ -1,3 +1,4 @@
+use sirsi_common::{ErrorKind, Retryable};
 use thiserror::Error;
 use tonic::Status;
 
@@ -67,4 +68,65 @@ impl From<ContainerError> for Status {
     }
 }
 
+impl Retryable for ContainerError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            ContainerError::Kubernetes(kube::Error::Api(response)) => {
+                ErrorKind::from_http_status(response.code)
+            }
+            ContainerError::Kubernetes(kube::Error::HyperError(_))
+            | ContainerError::Kubernetes(kube::Error::Service(_)) => ErrorKind::ProviderOutage,
+            ContainerError::Kubernetes(_) => ErrorKind::Internal,
+            ContainerError::Database(sqlx::Error::RowNotFound) => ErrorKind::NotFound,
+            ContainerError::Database(sqlx::Error::PoolTimedOut)
+            | ContainerError::Database(sqlx::Error::Io(_)) => ErrorKind::ProviderOutage,
+            ContainerError::Database(_) => ErrorKind::Internal,
+            ContainerError::Network(_) => ErrorKind::ProviderOutage,
+            ContainerError::Validation(_) => ErrorKind::InvalidInput,
+            ContainerError::NotFound(_) => ErrorKind::NotFound,
+            ContainerError::Permission(_) => ErrorKind::AuthFailure,
+            ContainerError::Config(_) => ErrorKind::InvalidInput,
+            ContainerError::Registry(_)
+            | ContainerError::Platform(_)
+            | ContainerError::Deployment(_)
+            | ContainerError::ServiceMesh(_)
+            | ContainerError::OCI(_)
+            | ContainerError::Service(_)
+            | ContainerError::Internal(_) => ErrorKind::Internal,
+        }
+    }
+}
+
 pub type ContainerResult<T> = Result<T, ContainerError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kube::error::ErrorResponse;
+
+    fn api_error(code: u16, reason: &str) -> ContainerError {
+        ContainerError::Kubernetes(kube::Error::Api(ErrorResponse {
+            status: "Failure".to_string(),
+            message: reason.to_string(),
+            reason: reason.to_string(),
+            code,
+        }))
+    }
+
+    #[test]
+    fn test_kubernetes_errors_classify() {
+        assert_eq!(api_error(429, "TooManyRequests").kind(), ErrorKind::Throttled);
+        assert!(api_error(429, "TooManyRequests").is_retryable());
+        assert_eq!(api_error(409, "AlreadyExists").kind(), ErrorKind::Conflict);
+        assert_eq!(api_error(404, "NotFound").kind(), ErrorKind::NotFound);
+        assert_eq!(api_error(403, "Forbidden").kind(), ErrorKind::AuthFailure);
+        assert!(api_error(503, "ServiceUnavailable").is_retryable());
+    }
+
+    #[test]
+    fn test_domain_errors_classify() {
+        assert!(ContainerError::Network("connection reset".into()).is_retryable());
+        assert!(!ContainerError::Validation("bad image reference".into()).is_retryable());
+        assert_eq!(ContainerError::Database(sqlx::Error::PoolTimedOut).kind(), ErrorKind::ProviderOutage);
+    }
+}
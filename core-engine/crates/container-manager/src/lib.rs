@@ -1,9 +1,11 @@
 pub mod error;
+pub mod network;
 pub mod platform;
 pub mod registry;
+pub mod runtime;
 pub mod service;
 pub mod mesh;
 
 pub use error::{ContainerError, ContainerResult};
-pub use platform::{KubernetesClient, KubernetesConfig};
+pub use platform::{ClusterManager, KubeClusterAccess, KubernetesClient, KubernetesConfig};
 pub use service::ContainerService;
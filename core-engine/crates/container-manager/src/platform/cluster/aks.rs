@@ -0,0 +1,353 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use base64::Engine;
+use serde_json::{json, Value};
+
+use super::{
+    int, missing, text, CloudApi, CloudProvider, ClusterConfig, ClusterInfo, ClusterProvisioner, ClusterStatus,
+    HttpMethod, NodePool, NodePoolInfo, Operation, OperationKind, OperationRef, OperationStatus, Surge, SurgeSettings,
+};
+use crate::error::{ContainerError, ContainerResult};
+
+const API_VERSION: &str = "2024-02-01";
+
+// The subscription, resource group and region managed clusters live in
+#[derive(Debug, Clone)]
+pub struct AksTarget {
+    pub subscription_id: String,
+    pub resource_group: String,
+    pub location: String,
+}
+
+// Talks to the ARM managedClusters API. ARM reports progress on the resource itself, so
+// operations are tracked through `provisioningState` rather than a separate operation id.
+pub struct AksProvisioner {
+    api: Arc<dyn CloudApi>,
+    target: AksTarget,
+}
+
+impl AksProvisioner {
+    pub fn new(api: Arc<dyn CloudApi>, target: AksTarget) -> Self {
+        Self { api, target }
+    }
+
+    fn cluster_path(&self, cluster: &str) -> String {
+        format!(
+            "/subscriptions/{}/resourceGroups/{}/providers/Microsoft.ContainerService/managedClusters/{}",
+            self.target.subscription_id, self.target.resource_group, cluster
+        )
+    }
+
+    fn pool_path(&self, cluster: &str, pool: &str) -> String {
+        format!("{}/agentPools/{}", self.cluster_path(cluster), pool)
+    }
+
+    fn url(path: &str) -> String {
+        format!("{}?api-version={}", path, API_VERSION)
+    }
+
+    fn status(value: Option<&str>) -> ClusterStatus {
+        match value {
+            Some("Creating") => ClusterStatus::Provisioning,
+            Some("Succeeded") => ClusterStatus::Running,
+            Some("Updating" | "Upgrading" | "Scaling") => ClusterStatus::Updating,
+            Some("Deleting") => ClusterStatus::Deleting,
+            Some("Failed" | "Canceled") => ClusterStatus::Failed,
+            _ => ClusterStatus::Unknown,
+        }
+    }
+
+    fn surge(surge: Surge) -> String {
+        match surge {
+            Surge::Nodes(n) => n.to_string(),
+            Surge::Percent(p) => format!("{}%", p),
+        }
+    }
+
+    fn upgrade_settings(surge: &SurgeSettings) -> Value {
+        json!({ "maxSurge": Self::surge(surge.max_surge), "maxUnavailable": Self::surge(surge.max_unavailable) })
+    }
+
+    fn pool_properties(pool: &NodePool, version: &str, system: bool, subnet: Option<&String>) -> Value {
+        let mut properties = json!({
+            "count": pool.desired_size,
+            "vmSize": pool.instance_type,
+            "mode": if system { "System" } else { "User" },
+            "orchestratorVersion": version,
+            "nodeLabels": pool.labels,
+            "upgradeSettings": Self::upgrade_settings(&pool.surge),
+        });
+        if pool.min_size != pool.max_size {
+            properties["enableAutoScaling"] = json!(true);
+            properties["minCount"] = json!(pool.min_size);
+            properties["maxCount"] = json!(pool.max_size);
+        }
+        if let Some(size) = pool.root_volume_size {
+            properties["osDiskSizeGB"] = json!(size);
+        }
+        if let Some(subnet) = subnet {
+            properties["vnetSubnetID"] = json!(subnet);
+        }
+        properties
+    }
+
+    fn pool_info(name: String, properties: &Value) -> NodePoolInfo {
+        let count = int(properties, &["count"]);
+        NodePoolInfo {
+            name,
+            version: text(properties, &["currentOrchestratorVersion"]).or_else(|| text(properties, &["orchestratorVersion"])),
+            instance_type: text(properties, &["vmSize"]),
+            min_size: int(properties, &["minCount"]).or(count),
+            max_size: int(properties, &["maxCount"]).or(count),
+            desired_size: count,
+            status: Self::status(text(properties, &["provisioningState"]).as_deref()),
+        }
+    }
+
+    async fn get(&self, path: &str) -> ContainerResult<Option<Value>> {
+        self.api.call(HttpMethod::Get, &Self::url(path), None).await
+    }
+
+    async fn put(&self, path: &str, body: Value) -> ContainerResult<()> {
+        self.api.call(HttpMethod::Put, &Self::url(path), Some(body)).await.map(|_| ())
+    }
+
+    // ARM PUTs replace the whole resource, so edits start from what is there now
+    async fn edit_pool(&self, cluster: &str, pool: &str, edit: impl FnOnce(&mut Value)) -> ContainerResult<()> {
+        let path = self.pool_path(cluster, pool);
+        let mut current = self
+            .get(&path)
+            .await?
+            .ok_or_else(|| ContainerError::NotFound(format!("Agent pool {} not found in {}", pool, cluster)))?;
+        let properties = current.get_mut("properties").ok_or_else(|| missing("properties"))?;
+        edit(properties);
+        self.put(&path, json!({ "properties": properties })).await
+    }
+}
+
+#[async_trait]
+impl ClusterProvisioner for AksProvisioner {
+    fn provider(&self) -> CloudProvider {
+        CloudProvider::Aks
+    }
+
+    async fn create_cluster(&self, config: &ClusterConfig) -> ContainerResult<Operation> {
+        let network = &config.network;
+        let subnet = network.subnet_ids.first();
+        let pools: Vec<Value> = config
+            .node_pools
+            .iter()
+            .enumerate()
+            .map(|(i, pool)| {
+                let mut profile = Self::pool_properties(pool, &config.version, i == 0, subnet);
+                profile["name"] = json!(pool.name);
+                profile
+            })
+            .collect();
+        let mut properties = json!({
+            "kubernetesVersion": config.version,
+            "dnsPrefix": config.name,
+            "agentPoolProfiles": pools,
+            "networkProfile": {
+                "networkPlugin": "azure",
+                "podCidr": network.pod_cidr,
+                "serviceCidr": network.service_cidr,
+            },
+            "apiServerAccessProfile": {
+                "enablePrivateCluster": network.private_endpoint,
+                "authorizedIPRanges": network.authorized_cidrs,
+            },
+        });
+        if !config.rbac.admin_groups.is_empty() {
+            properties["aadProfile"] = json!({ "managed": true, "adminGroupObjectIDs": config.rbac.admin_groups });
+        }
+        let body = json!({
+            "location": self.target.location,
+            "tags": config.tags,
+            "identity": { "type": "SystemAssigned" },
+            "properties": properties,
+        });
+        self.put(&self.cluster_path(&config.name), body).await?;
+        Ok(OperationRef::new(OperationKind::CreateCluster, &config.name).status(OperationStatus::Running))
+    }
+
+    async fn describe_cluster(&self, name: &str) -> ContainerResult<Option<ClusterInfo>> {
+        let Some(cluster) = self.get(&self.cluster_path(name)).await? else {
+            return Ok(None);
+        };
+        let properties = cluster.get("properties").ok_or_else(|| missing("properties"))?;
+        let node_pools = properties
+            .get("agentPoolProfiles")
+            .and_then(Value::as_array)
+            .map(|pools| pools.iter().map(|p| Self::pool_info(text(p, &["name"]).unwrap_or_default(), p)).collect())
+            .unwrap_or_default();
+        let version = text(properties, &["currentKubernetesVersion"])
+            .or_else(|| text(properties, &["kubernetesVersion"]))
+            .ok_or_else(|| missing("kubernetesVersion"))?;
+        Ok(Some(ClusterInfo {
+            name: name.to_string(),
+            provider: CloudProvider::Aks,
+            version,
+            status: Self::status(text(properties, &["provisioningState"]).as_deref()),
+            status_message: None,
+            endpoint: text(properties, &["fqdn"]).or_else(|| text(properties, &["privateFQDN"])).map(|f| format!("https://{}:443", f)),
+            node_pools,
+        }))
+    }
+
+    async fn operation(&self, id: &str) -> ContainerResult<Operation> {
+        let op = OperationRef::parse(id)?;
+        let path = match op.kind {
+            OperationKind::CreateCluster | OperationKind::UpgradeControlPlane | OperationKind::DeleteCluster => {
+                self.cluster_path(&op.cluster)
+            }
+            _ => self.pool_path(&op.cluster, op.pool()?),
+        };
+        let deleting = matches!(op.kind, OperationKind::DeleteCluster | OperationKind::DeleteNodePool);
+        let status = match self.get(&path).await? {
+            None if deleting => OperationStatus::Succeeded,
+            None => OperationStatus::Failed("resource no longer exists".into()),
+            Some(_) if deleting => OperationStatus::Running,
+            Some(resource) => match Self::status(text(&resource, &["properties", "provisioningState"]).as_deref()) {
+                ClusterStatus::Running => OperationStatus::Succeeded,
+                ClusterStatus::Failed => OperationStatus::Failed("provisioning failed".into()),
+                _ => OperationStatus::Running,
+            },
+        };
+        Ok(op.status(status))
+    }
+
+    async fn kubeconfig(&self, name: &str) -> ContainerResult<String> {
+        let path = Self::url(&format!("{}/listClusterAdminCredential", self.cluster_path(name)));
+        let response = self.api.call(HttpMethod::Post, &path, None).await?.ok_or_else(|| missing("credentials"))?;
+        let encoded = response.pointer("/kubeconfigs/0/value").and_then(Value::as_str).ok_or_else(|| missing("kubeconfigs"))?;
+        let decoded = base64::engine::general_purpose::STANDARD
+            .decode(encoded)
+            .map_err(|e| ContainerError::Platform(format!("AKS returned an undecodable kubeconfig: {}", e)))?;
+        String::from_utf8(decoded).map_err(|e| ContainerError::Platform(format!("AKS returned a non-UTF-8 kubeconfig: {}", e)))
+    }
+
+    async fn add_node_pool(&self, cluster: &str, pool: &NodePool) -> ContainerResult<Operation> {
+        let current = self.get(&self.cluster_path(cluster)).await?.ok_or_else(|| missing("cluster"))?;
+        let version = text(&current, &["properties", "kubernetesVersion"]).ok_or_else(|| missing("kubernetesVersion"))?;
+        let subnet = current.pointer("/properties/agentPoolProfiles/0/vnetSubnetID").and_then(Value::as_str).map(str::to_string);
+        let properties = Self::pool_properties(pool, &version, false, subnet.as_ref());
+        self.put(&self.pool_path(cluster, &pool.name), json!({ "properties": properties })).await?;
+        Ok(OperationRef::new(OperationKind::AddNodePool, cluster).with_pool(&pool.name).status(OperationStatus::Running))
+    }
+
+    async fn resize_node_pool(&self, cluster: &str, pool: &str, desired_size: i32) -> ContainerResult<Operation> {
+        self.edit_pool(cluster, pool, |p| p["count"] = json!(desired_size)).await?;
+        Ok(OperationRef::new(OperationKind::ResizeNodePool, cluster).with_pool(pool).status(OperationStatus::Running))
+    }
+
+    async fn delete_node_pool(&self, cluster: &str, pool: &str) -> ContainerResult<Operation> {
+        self.api.call(HttpMethod::Delete, &Self::url(&self.pool_path(cluster, pool)), None).await?;
+        Ok(OperationRef::new(OperationKind::DeleteNodePool, cluster).with_pool(pool).status(OperationStatus::Running))
+    }
+
+    async fn upgrade_control_plane(&self, cluster: &str, version: &str) -> ContainerResult<Operation> {
+        let path = self.cluster_path(cluster);
+        let mut current = self.get(&path).await?.ok_or_else(|| missing("cluster"))?;
+        // Pools keep their own orchestratorVersion, so only the control plane moves
+        current["properties"]["kubernetesVersion"] = json!(version);
+        self.put(&path, current).await?;
+        Ok(OperationRef::new(OperationKind::UpgradeControlPlane, cluster).status(OperationStatus::Running))
+    }
+
+    async fn upgrade_node_pool(&self, cluster: &str, pool: &str, version: &str, surge: &SurgeSettings) -> ContainerResult<Operation> {
+        let settings = Self::upgrade_settings(surge);
+        self.edit_pool(cluster, pool, |p| {
+            p["orchestratorVersion"] = json!(version);
+            p["upgradeSettings"] = settings;
+        })
+        .await?;
+        Ok(OperationRef::new(OperationKind::UpgradeNodePool, cluster).with_pool(pool).status(OperationStatus::Running))
+    }
+
+    async fn delete_cluster(&self, name: &str) -> ContainerResult<Operation> {
+        self.api.call(HttpMethod::Delete, &Self::url(&self.cluster_path(name)), None).await?;
+        Ok(OperationRef::new(OperationKind::DeleteCluster, name).status(OperationStatus::Running))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use chrono::Utc;
+    use sirsi_key_vault::secret::{InMemorySecretManager, Secret, SecretManager, SecretValue};
+
+    use super::*;
+    use crate::platform::cluster::testing::{FakeAccess, ScriptedApi};
+    use crate::platform::cluster::ClusterManager;
+
+    const CLUSTER: &str = "/subscriptions/sub-1/resourceGroups/platform/providers/Microsoft.ContainerService/managedClusters/prod";
+
+    fn url(path: &str) -> String {
+        format!("{}?api-version={}", path, API_VERSION)
+    }
+
+    #[tokio::test]
+    async fn test_delete_requires_empty_cluster_or_force() {
+        let api = Arc::new(ScriptedApi::default());
+        let cluster = json!({ "properties": {
+            "provisioningState": "Succeeded",
+            "kubernetesVersion": "1.29.2",
+            "fqdn": "prod-abc.hcp.westeurope.azmk8s.io",
+            "agentPoolProfiles": [{ "name": "system", "count": 3, "provisioningState": "Succeeded" }],
+        }});
+        api.respond(HttpMethod::Get, &url(CLUSTER), vec![Some(cluster.clone()), Some(cluster), None]);
+        api.respond(HttpMethod::Delete, &url(CLUSTER), vec![None]);
+        let kubeconfig = "apiVersion: v1\nkind: Config\n";
+        let encoded = base64::engine::general_purpose::STANDARD.encode(kubeconfig);
+        api.respond(
+            HttpMethod::Post,
+            &url(&format!("{}/listClusterAdminCredential", CLUSTER)),
+            vec![Some(json!({ "kubeconfigs": [{ "name": "clusterAdmin", "value": encoded }] }))],
+        );
+
+        let target = AksTarget { subscription_id: "sub-1".into(), resource_group: "platform".into(), location: "westeurope".into() };
+        let provisioner = Arc::new(AksProvisioner::new(api.clone(), target));
+        assert_eq!(provisioner.kubeconfig("prod").await.unwrap(), kubeconfig);
+
+        let secrets = Arc::new(InMemorySecretManager::new());
+        let access = Arc::new(FakeAccess::default());
+        access.workloads.lock().unwrap().push("shop/Deployment/checkout".into());
+        let manager = ClusterManager::new(provisioner, secrets.clone())
+            .with_access(access.clone())
+            .with_poll_interval(std::time::Duration::from_millis(1));
+
+        let now = Utc::now();
+        secrets
+            .create_secret(Secret {
+                id: manager.kubeconfig_secret_id("prod"),
+                name: "prod kubeconfig".into(),
+                description: None,
+                value: SecretValue::Plain(kubeconfig.into()),
+                version: 0,
+                created_at: now,
+                updated_at: now,
+                expires_at: None,
+                metadata: HashMap::new(),
+                labels: HashMap::new(),
+                rotation_policy: None,
+            })
+            .await
+            .unwrap();
+
+        let err = manager.delete_cluster("prod", false).await.unwrap_err();
+        assert!(err.to_string().contains("shop/Deployment/checkout"));
+        assert!(api.calls_to(HttpMethod::Delete, &url(CLUSTER)).is_empty());
+
+        access.workloads.lock().unwrap().clear();
+        let op = manager.delete_cluster("prod", false).await.unwrap();
+        assert_eq!(api.calls_to(HttpMethod::Delete, &url(CLUSTER)).len(), 1);
+        assert!(manager.kubeconfig("prod").await.is_err());
+
+        // Once ARM stops returning the cluster the delete has finished
+        let done = manager.wait(&op, std::time::Duration::from_secs(1)).await.unwrap();
+        assert_eq!(done.status, OperationStatus::Succeeded);
+    }
+}
@@ -0,0 +1,378 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde_json::{json, Value};
+
+use super::{
+    int, kubeconfig_yaml, missing, text, CloudApi, CloudProvider, ClusterConfig, ClusterInfo, ClusterProvisioner,
+    ClusterStatus, HttpMethod, NodePool, NodePoolInfo, Operation, OperationKind, OperationRef, OperationStatus, Surge,
+    SurgeSettings,
+};
+use crate::error::ContainerResult;
+
+// Where EKS clusters are created and the IAM roles they run as
+#[derive(Debug, Clone)]
+pub struct EksTarget {
+    pub region: String,
+    pub cluster_role_arn: String,
+    pub node_role_arn: String,
+}
+
+// Talks to the EKS REST API. EKS creates node groups separately from the control plane, so
+// a new cluster reports its pools missing until `ClusterManager` adds them.
+pub struct EksProvisioner {
+    api: Arc<dyn CloudApi>,
+    target: EksTarget,
+}
+
+impl EksProvisioner {
+    pub fn new(api: Arc<dyn CloudApi>, target: EksTarget) -> Self {
+        Self { api, target }
+    }
+
+    fn status(value: Option<&str>) -> ClusterStatus {
+        match value {
+            Some("CREATING") => ClusterStatus::Provisioning,
+            Some("ACTIVE") => ClusterStatus::Running,
+            Some("UPDATING") => ClusterStatus::Updating,
+            Some("DELETING") => ClusterStatus::Deleting,
+            Some("FAILED" | "CREATE_FAILED" | "DELETE_FAILED" | "DEGRADED") => ClusterStatus::Failed,
+            _ => ClusterStatus::Unknown,
+        }
+    }
+
+    // EKS drains by `maxUnavailable` and has no surge knob; it must be at least one node
+    fn update_config(surge: &SurgeSettings) -> Value {
+        match surge.max_unavailable {
+            Surge::Percent(p) if p > 0 => json!({ "maxUnavailablePercentage": p }),
+            Surge::Nodes(n) => json!({ "maxUnavailable": n.max(1) }),
+            Surge::Percent(_) => json!({ "maxUnavailable": 1 }),
+        }
+    }
+
+    async fn node_groups(&self, cluster: &str) -> ContainerResult<Vec<NodePoolInfo>> {
+        let list = self.api.call(HttpMethod::Get, &format!("/clusters/{}/node-groups", cluster), None).await?;
+        let names: Vec<String> = list
+            .as_ref()
+            .and_then(|l| l.get("nodegroups"))
+            .and_then(Value::as_array)
+            .map(|names| names.iter().filter_map(|n| n.as_str().map(str::to_string)).collect())
+            .unwrap_or_default();
+        let mut pools = Vec::with_capacity(names.len());
+        for name in names {
+            let path = format!("/clusters/{}/node-groups/{}", cluster, name);
+            let Some(group) = self.api.call(HttpMethod::Get, &path, None).await? else { continue };
+            let group = group.get("nodegroup").ok_or_else(|| missing("nodegroup"))?;
+            pools.push(NodePoolInfo {
+                name,
+                version: text(group, &["version"]),
+                instance_type: group.pointer("/instanceTypes/0").and_then(Value::as_str).map(str::to_string),
+                min_size: int(group, &["scalingConfig", "minSize"]),
+                max_size: int(group, &["scalingConfig", "maxSize"]),
+                desired_size: int(group, &["scalingConfig", "desiredSize"]),
+                status: Self::status(text(group, &["status"]).as_deref()),
+            });
+        }
+        Ok(pools)
+    }
+
+    async fn update(&self, op: OperationRef, path: String, body: Value) -> ContainerResult<Operation> {
+        let response = self.api.call(HttpMethod::Post, &path, Some(body)).await?.ok_or_else(|| missing("update"))?;
+        let id = text(&response, &["update", "id"]).ok_or_else(|| missing("update.id"))?;
+        Ok(op.with_token(id).status(OperationStatus::Running))
+    }
+}
+
+#[async_trait]
+impl ClusterProvisioner for EksProvisioner {
+    fn provider(&self) -> CloudProvider {
+        CloudProvider::Eks
+    }
+
+    async fn create_cluster(&self, config: &ClusterConfig) -> ContainerResult<Operation> {
+        let network = &config.network;
+        let mut vpc = json!({
+            "subnetIds": network.subnet_ids,
+            "endpointPrivateAccess": true,
+            "endpointPublicAccess": !network.private_endpoint,
+        });
+        if !network.authorized_cidrs.is_empty() {
+            vpc["publicAccessCidrs"] = json!(network.authorized_cidrs);
+        }
+        let mut body = json!({
+            "name": config.name,
+            "version": config.version,
+            "roleArn": self.target.cluster_role_arn,
+            "resourcesVpcConfig": vpc,
+            "tags": config.tags,
+        });
+        if let Some(cidr) = &network.service_cidr {
+            body["kubernetesNetworkConfig"] = json!({ "serviceIpv4Cidr": cidr });
+        }
+        self.api.call(HttpMethod::Post, "/clusters", Some(body)).await?;
+        Ok(OperationRef::new(OperationKind::CreateCluster, &config.name).status(OperationStatus::Running))
+    }
+
+    async fn describe_cluster(&self, name: &str) -> ContainerResult<Option<ClusterInfo>> {
+        let Some(response) = self.api.call(HttpMethod::Get, &format!("/clusters/{}", name), None).await? else {
+            return Ok(None);
+        };
+        let cluster = response.get("cluster").ok_or_else(|| missing("cluster"))?;
+        let status = Self::status(text(cluster, &["status"]).as_deref());
+        let node_pools = if status == ClusterStatus::Running || status == ClusterStatus::Updating {
+            self.node_groups(name).await?
+        } else {
+            Vec::new()
+        };
+        Ok(Some(ClusterInfo {
+            name: name.to_string(),
+            provider: CloudProvider::Eks,
+            version: text(cluster, &["version"]).ok_or_else(|| missing("cluster.version"))?,
+            status,
+            status_message: cluster.pointer("/health/issues/0/message").and_then(Value::as_str).map(str::to_string),
+            endpoint: text(cluster, &["endpoint"]),
+            node_pools,
+        }))
+    }
+
+    async fn operation(&self, id: &str) -> ContainerResult<Operation> {
+        let op = OperationRef::parse(id)?;
+        let cluster_path = format!("/clusters/{}", op.cluster);
+        let status = match (op.kind, &op.token) {
+            (_, Some(update)) => {
+                let mut path = format!("{}/updates/{}", cluster_path, update);
+                if let Some(pool) = &op.pool {
+                    path.push_str(&format!("?nodegroupName={}", pool));
+                }
+                let response = self.api.call(HttpMethod::Get, &path, None).await?.ok_or_else(|| missing("update"))?;
+                match text(&response, &["update", "status"]).as_deref() {
+                    Some("Successful") => OperationStatus::Succeeded,
+                    Some("Failed" | "Cancelled") => OperationStatus::Failed(
+                        response
+                            .pointer("/update/errors/0/errorMessage")
+                            .and_then(Value::as_str)
+                            .unwrap_or("update failed")
+                            .to_string(),
+                    ),
+                    _ => OperationStatus::Running,
+                }
+            }
+            (OperationKind::CreateCluster, None) => {
+                let response = self.api.call(HttpMethod::Get, &cluster_path, None).await?;
+                match Self::status(response.as_ref().and_then(|r| text(r, &["cluster", "status"])).as_deref()) {
+                    ClusterStatus::Running => OperationStatus::Succeeded,
+                    ClusterStatus::Failed => OperationStatus::Failed("cluster creation failed".into()),
+                    _ => OperationStatus::Running,
+                }
+            }
+            (OperationKind::AddNodePool, None) => {
+                let path = format!("{}/node-groups/{}", cluster_path, op.pool()?);
+                let response = self.api.call(HttpMethod::Get, &path, None).await?;
+                match Self::status(response.as_ref().and_then(|r| text(r, &["nodegroup", "status"])).as_deref()) {
+                    ClusterStatus::Running => OperationStatus::Succeeded,
+                    ClusterStatus::Failed => OperationStatus::Failed("node group creation failed".into()),
+                    _ => OperationStatus::Running,
+                }
+            }
+            (OperationKind::DeleteNodePool, None) => {
+                let path = format!("{}/node-groups/{}", cluster_path, op.pool()?);
+                match self.api.call(HttpMethod::Get, &path, None).await? {
+                    None => OperationStatus::Succeeded,
+                    Some(_) => OperationStatus::Running,
+                }
+            }
+            // The control plane can only be deleted once its node groups are gone, so the
+            // cluster delete is issued from here when the last one disappears
+            (OperationKind::DeleteCluster, None) => match self.api.call(HttpMethod::Get, &cluster_path, None).await? {
+                None => OperationStatus::Succeeded,
+                Some(cluster) if text(&cluster, &["cluster", "status"]).as_deref() == Some("DELETING") => OperationStatus::Running,
+                Some(_) => {
+                    if self.node_groups(&op.cluster).await?.is_empty() {
+                        self.api.call(HttpMethod::Delete, &cluster_path, None).await?;
+                    }
+                    OperationStatus::Running
+                }
+            },
+            _ => OperationStatus::Failed(format!("operation {} carries no update id", id)),
+        };
+        Ok(op.status(status))
+    }
+
+    async fn kubeconfig(&self, name: &str) -> ContainerResult<String> {
+        let response = self
+            .api
+            .call(HttpMethod::Get, &format!("/clusters/{}", name), None)
+            .await?
+            .ok_or_else(|| missing("cluster"))?;
+        let endpoint = text(&response, &["cluster", "endpoint"]).ok_or_else(|| missing("cluster.endpoint"))?;
+        let ca = text(&response, &["cluster", "certificateAuthority", "data"]).ok_or_else(|| missing("cluster.certificateAuthority"))?;
+        let args = ["eks", "get-token", "--cluster-name", name, "--region", &self.target.region];
+        Ok(kubeconfig_yaml(name, &endpoint, &ca, "aws", &args))
+    }
+
+    async fn add_node_pool(&self, cluster: &str, pool: &NodePool) -> ContainerResult<Operation> {
+        let mut body = json!({
+            "nodegroupName": pool.name,
+            "instanceTypes": [pool.instance_type],
+            "scalingConfig": { "minSize": pool.min_size, "maxSize": pool.max_size, "desiredSize": pool.desired_size },
+            "nodeRole": self.target.node_role_arn,
+            "labels": pool.labels,
+            "updateConfig": Self::update_config(&pool.surge),
+        });
+        if let Some(size) = pool.root_volume_size {
+            body["diskSize"] = json!(size);
+        }
+        // Node groups land in the subnets the control plane was given
+        let cluster_info = self.api.call(HttpMethod::Get, &format!("/clusters/{}", cluster), None).await?;
+        body["subnets"] = cluster_info
+            .as_ref()
+            .and_then(|c| c.pointer("/cluster/resourcesVpcConfig/subnetIds"))
+            .cloned()
+            .unwrap_or_else(|| json!([]));
+        self.api.call(HttpMethod::Post, &format!("/clusters/{}/node-groups", cluster), Some(body)).await?;
+        Ok(OperationRef::new(OperationKind::AddNodePool, cluster).with_pool(&pool.name).status(OperationStatus::Running))
+    }
+
+    async fn resize_node_pool(&self, cluster: &str, pool: &str, desired_size: i32) -> ContainerResult<Operation> {
+        let op = OperationRef::new(OperationKind::ResizeNodePool, cluster).with_pool(pool);
+        let path = format!("/clusters/{}/node-groups/{}/update-config", cluster, pool);
+        self.update(op, path, json!({ "scalingConfig": { "desiredSize": desired_size } })).await
+    }
+
+    async fn delete_node_pool(&self, cluster: &str, pool: &str) -> ContainerResult<Operation> {
+        self.api.call(HttpMethod::Delete, &format!("/clusters/{}/node-groups/{}", cluster, pool), None).await?;
+        Ok(OperationRef::new(OperationKind::DeleteNodePool, cluster).with_pool(pool).status(OperationStatus::Running))
+    }
+
+    async fn upgrade_control_plane(&self, cluster: &str, version: &str) -> ContainerResult<Operation> {
+        let op = OperationRef::new(OperationKind::UpgradeControlPlane, cluster);
+        self.update(op, format!("/clusters/{}/updates", cluster), json!({ "version": version })).await
+    }
+
+    // The drain rate comes from the node group's `updateConfig`, which EKS does not accept on
+    // `update-version`; it was set from `NodePool::surge` when the pool was created
+    async fn upgrade_node_pool(&self, cluster: &str, pool: &str, version: &str, _surge: &SurgeSettings) -> ContainerResult<Operation> {
+        let op = OperationRef::new(OperationKind::UpgradeNodePool, cluster).with_pool(pool);
+        let path = format!("/clusters/{}/node-groups/{}/update-version", cluster, pool);
+        self.update(op, path, json!({ "version": version })).await
+    }
+
+    async fn delete_cluster(&self, name: &str) -> ContainerResult<Operation> {
+        let pools = self.node_groups(name).await?;
+        for pool in &pools {
+            self.api.call(HttpMethod::Delete, &format!("/clusters/{}/node-groups/{}", name, pool.name), None).await?;
+        }
+        if pools.is_empty() {
+            self.api.call(HttpMethod::Delete, &format!("/clusters/{}", name), None).await?;
+        }
+        Ok(OperationRef::new(OperationKind::DeleteCluster, name).status(OperationStatus::Running))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use sirsi_key_vault::secret::InMemorySecretManager;
+
+    use super::*;
+    use crate::platform::cluster::testing::{FakeAccess, ScriptedApi};
+    use crate::platform::cluster::{ClusterManager, ClusterNetwork, RbacBootstrap};
+
+    fn target() -> EksTarget {
+        EksTarget {
+            region: "us-east-1".into(),
+            cluster_role_arn: "arn:aws:iam::123:role/eks-cluster".into(),
+            node_role_arn: "arn:aws:iam::123:role/eks-node".into(),
+        }
+    }
+
+    fn cluster(status: &str) -> Option<Value> {
+        Some(json!({ "cluster": {
+            "name": "prod",
+            "version": "1.29",
+            "status": status,
+            "endpoint": "https://ABC.gr7.us-east-1.eks.amazonaws.com",
+            "certificateAuthority": { "data": "Q0EK" },
+            "resourcesVpcConfig": { "subnetIds": ["subnet-a", "subnet-b"] },
+        }}))
+    }
+
+    #[tokio::test]
+    async fn test_provision_polls_until_ready_and_stores_kubeconfig() {
+        let api = Arc::new(ScriptedApi::default());
+        // Not visible yet, then creating, then active from there on
+        api.respond(HttpMethod::Get, "/clusters/prod", vec![None, None, cluster("CREATING"), cluster("ACTIVE")]);
+        api.respond(HttpMethod::Post, "/clusters", vec![cluster("CREATING")]);
+        api.respond(HttpMethod::Post, "/clusters/prod/node-groups", vec![Some(json!({}))]);
+        api.respond(
+            HttpMethod::Get,
+            "/clusters/prod/node-groups",
+            vec![Some(json!({ "nodegroups": [] })), Some(json!({ "nodegroups": ["system"] }))],
+        );
+        let group = |status: &str| {
+            Some(json!({ "nodegroup": {
+                "version": "1.29",
+                "instanceTypes": ["m5.large"],
+                "scalingConfig": { "minSize": 2, "maxSize": 4, "desiredSize": 2 },
+                "status": status,
+            }}))
+        };
+        api.respond(HttpMethod::Get, "/clusters/prod/node-groups/system", vec![group("CREATING"), group("ACTIVE")]);
+
+        let secrets = Arc::new(InMemorySecretManager::new());
+        let access = Arc::new(FakeAccess::default());
+        let manager = ClusterManager::new(Arc::new(EksProvisioner::new(api.clone(), target())), secrets)
+            .with_access(access.clone())
+            .with_poll_interval(Duration::from_millis(1));
+        let config = ClusterConfig {
+            name: "prod".into(),
+            version: "1.29".into(),
+            node_pools: vec![NodePool::new("system", "m5.large", 2, 4)],
+            network: ClusterNetwork { subnet_ids: vec!["subnet-a".into(), "subnet-b".into()], ..Default::default() },
+            rbac: RbacBootstrap { admin_groups: vec!["platform-admins".into()], bindings: Vec::new() },
+            tags: Default::default(),
+        };
+
+        let info = manager.provision(&config).await.unwrap();
+        assert_eq!(info.status, ClusterStatus::Running);
+        assert_eq!(info.node_pools[0].desired_size, Some(2));
+
+        // The node group is requested once, after the control plane went active
+        let created = api.calls_to(HttpMethod::Post, "/clusters/prod/node-groups");
+        assert_eq!(created.len(), 1);
+        let body = created[0].as_ref().unwrap();
+        assert_eq!(body["subnets"], json!(["subnet-a", "subnet-b"]));
+        assert_eq!(body["updateConfig"], json!({ "maxUnavailable": 1 }));
+
+        let kubeconfig = manager.kubeconfig("prod").await.unwrap();
+        assert!(kubeconfig.contains("server: https://ABC.gr7.us-east-1.eks.amazonaws.com"));
+        assert!(kubeconfig.contains("- get-token"));
+        assert_eq!(access.applied.lock().unwrap()[0].subjects, vec!["platform-admins".to_string()]);
+
+        // A second create is refused while the cluster exists
+        assert!(manager.create_cluster(&config).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_failed_cluster_stops_polling() {
+        let api = Arc::new(ScriptedApi::default());
+        api.respond(HttpMethod::Get, "/clusters/prod", vec![None, cluster("CREATING"), cluster("FAILED")]);
+        api.respond(HttpMethod::Post, "/clusters", vec![cluster("CREATING")]);
+        let manager = ClusterManager::new(Arc::new(EksProvisioner::new(api.clone(), target())), Arc::new(InMemorySecretManager::new()))
+            .with_poll_interval(Duration::from_millis(1));
+        let config = ClusterConfig {
+            name: "prod".into(),
+            version: "1.29".into(),
+            node_pools: vec![NodePool::new("system", "m5.large", 1, 3)],
+            network: ClusterNetwork::default(),
+            rbac: RbacBootstrap::default(),
+            tags: Default::default(),
+        };
+
+        let err = manager.provision(&config).await.unwrap_err();
+        assert!(err.to_string().contains("failed to provision"));
+        assert!(api.calls_to(HttpMethod::Post, "/clusters/prod/node-groups").is_empty());
+        assert!(manager.kubeconfig("prod").await.is_err());
+    }
+}
@@ -0,0 +1,302 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde_json::{json, Value};
+
+use super::{
+    int, kubeconfig_yaml, missing, text, CloudApi, CloudProvider, ClusterConfig, ClusterInfo, ClusterProvisioner,
+    ClusterStatus, HttpMethod, NodePool, NodePoolInfo, Operation, OperationKind, OperationRef, OperationStatus, Surge,
+    SurgeSettings,
+};
+use crate::error::{ContainerError, ContainerResult};
+
+// The project and region or zone clusters are created in
+#[derive(Debug, Clone)]
+pub struct GkeTarget {
+    pub project: String,
+    pub location: String,
+}
+
+// Talks to the GKE v1 API. Every mutation returns a GKE operation, whose name becomes the
+// token in our operation id.
+pub struct GkeProvisioner {
+    api: Arc<dyn CloudApi>,
+    target: GkeTarget,
+}
+
+impl GkeProvisioner {
+    pub fn new(api: Arc<dyn CloudApi>, target: GkeTarget) -> Self {
+        Self { api, target }
+    }
+
+    fn location_path(&self) -> String {
+        format!("/v1/projects/{}/locations/{}", self.target.project, self.target.location)
+    }
+
+    fn cluster_path(&self, cluster: &str) -> String {
+        format!("{}/clusters/{}", self.location_path(), cluster)
+    }
+
+    fn pool_path(&self, cluster: &str, pool: &str) -> String {
+        format!("{}/nodePools/{}", self.cluster_path(cluster), pool)
+    }
+
+    fn status(value: Option<&str>) -> ClusterStatus {
+        match value {
+            Some("PROVISIONING") => ClusterStatus::Provisioning,
+            Some("RUNNING") => ClusterStatus::Running,
+            Some("RECONCILING") => ClusterStatus::Updating,
+            Some("STOPPING") => ClusterStatus::Deleting,
+            Some("ERROR" | "DEGRADED" | "RUNNING_WITH_ERROR") => ClusterStatus::Failed,
+            _ => ClusterStatus::Unknown,
+        }
+    }
+
+    // GKE counts surge in nodes only
+    fn upgrade_settings(surge: &SurgeSettings) -> ContainerResult<Value> {
+        match (surge.max_surge, surge.max_unavailable) {
+            (Surge::Nodes(max_surge), Surge::Nodes(max_unavailable)) => {
+                Ok(json!({ "strategy": "SURGE", "maxSurge": max_surge, "maxUnavailable": max_unavailable }))
+            }
+            _ => Err(ContainerError::Validation("GKE surge settings must be node counts, not percentages".into())),
+        }
+    }
+
+    fn node_pool(pool: &NodePool, version: Option<&str>) -> ContainerResult<Value> {
+        let mut config = json!({ "machineType": pool.instance_type, "labels": pool.labels });
+        if let Some(size) = pool.root_volume_size {
+            config["diskSizeGb"] = json!(size);
+        }
+        let mut body = json!({
+            "name": pool.name,
+            "initialNodeCount": pool.desired_size,
+            "config": config,
+            "upgradeSettings": Self::upgrade_settings(&pool.surge)?,
+            "management": { "autoUpgrade": false, "autoRepair": true },
+        });
+        if pool.min_size != pool.max_size {
+            body["autoscaling"] = json!({ "enabled": true, "minNodeCount": pool.min_size, "maxNodeCount": pool.max_size });
+        }
+        if let Some(version) = version {
+            body["version"] = json!(version);
+        }
+        Ok(body)
+    }
+
+    async fn submit(&self, op: OperationRef, method: HttpMethod, path: String, body: Option<Value>) -> ContainerResult<Operation> {
+        let response = self.api.call(method, &path, body).await?.ok_or_else(|| missing("operation"))?;
+        let name = text(&response, &["name"]).ok_or_else(|| missing("operation.name"))?;
+        Ok(op.with_token(name).status(OperationStatus::Running))
+    }
+}
+
+#[async_trait]
+impl ClusterProvisioner for GkeProvisioner {
+    fn provider(&self) -> CloudProvider {
+        CloudProvider::Gke
+    }
+
+    async fn create_cluster(&self, config: &ClusterConfig) -> ContainerResult<Operation> {
+        let network = &config.network;
+        let pools = config
+            .node_pools
+            .iter()
+            .map(|p| Self::node_pool(p, Some(&config.version)))
+            .collect::<ContainerResult<Vec<_>>>()?;
+        let mut cluster = json!({
+            "name": config.name,
+            "initialClusterVersion": config.version,
+            "nodePools": pools,
+            "resourceLabels": config.tags,
+            "ipAllocationPolicy": {
+                "useIpAliases": true,
+                "clusterIpv4CidrBlock": network.pod_cidr,
+                "servicesIpv4CidrBlock": network.service_cidr,
+            },
+            "releaseChannel": { "channel": "UNSPECIFIED" },
+        });
+        if let Some(id) = &network.network_id {
+            cluster["network"] = json!(id);
+        }
+        if let Some(subnet) = network.subnet_ids.first() {
+            cluster["subnetwork"] = json!(subnet);
+        }
+        if network.private_endpoint {
+            cluster["privateClusterConfig"] = json!({ "enablePrivateNodes": true, "enablePrivateEndpoint": true });
+        }
+        if !network.authorized_cidrs.is_empty() {
+            let blocks: Vec<Value> = network.authorized_cidrs.iter().map(|c| json!({ "cidrBlock": c })).collect();
+            cluster["masterAuthorizedNetworksConfig"] = json!({ "enabled": true, "cidrBlocks": blocks });
+        }
+        let op = OperationRef::new(OperationKind::CreateCluster, &config.name);
+        let path = format!("{}/clusters", self.location_path());
+        self.submit(op, HttpMethod::Post, path, Some(json!({ "cluster": cluster }))).await
+    }
+
+    async fn describe_cluster(&self, name: &str) -> ContainerResult<Option<ClusterInfo>> {
+        let Some(cluster) = self.api.call(HttpMethod::Get, &self.cluster_path(name), None).await? else {
+            return Ok(None);
+        };
+        let node_pools = cluster
+            .get("nodePools")
+            .and_then(Value::as_array)
+            .map(|pools| {
+                pools
+                    .iter()
+                    .map(|p| NodePoolInfo {
+                        name: text(p, &["name"]).unwrap_or_default(),
+                        version: text(p, &["version"]),
+                        instance_type: text(p, &["config", "machineType"]),
+                        min_size: int(p, &["autoscaling", "minNodeCount"]).or_else(|| int(p, &["initialNodeCount"])),
+                        max_size: int(p, &["autoscaling", "maxNodeCount"]).or_else(|| int(p, &["initialNodeCount"])),
+                        desired_size: int(p, &["initialNodeCount"]),
+                        status: Self::status(text(p, &["status"]).as_deref()),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok(Some(ClusterInfo {
+            name: name.to_string(),
+            provider: CloudProvider::Gke,
+            version: text(&cluster, &["currentMasterVersion"]).ok_or_else(|| missing("currentMasterVersion"))?,
+            status: Self::status(text(&cluster, &["status"]).as_deref()),
+            status_message: text(&cluster, &["statusMessage"]),
+            endpoint: text(&cluster, &["endpoint"]).map(|e| format!("https://{}", e)),
+            node_pools,
+        }))
+    }
+
+    async fn operation(&self, id: &str) -> ContainerResult<Operation> {
+        let op = OperationRef::parse(id)?;
+        let name = op.token.as_deref().ok_or_else(|| ContainerError::Validation(format!("Operation {} has no GKE operation", id)))?;
+        let path = format!("{}/operations/{}", self.location_path(), name);
+        let response = self.api.call(HttpMethod::Get, &path, None).await?.ok_or_else(|| missing("operation"))?;
+        let status = match text(&response, &["status"]).as_deref() {
+            Some("DONE") => match text(&response, &["error", "message"]).or_else(|| text(&response, &["statusMessage"])) {
+                Some(message) if !message.is_empty() => OperationStatus::Failed(message),
+                _ => OperationStatus::Succeeded,
+            },
+            Some("ABORTING") => OperationStatus::Failed("operation aborted".into()),
+            _ => OperationStatus::Running,
+        };
+        Ok(op.status(status))
+    }
+
+    async fn kubeconfig(&self, name: &str) -> ContainerResult<String> {
+        let cluster = self
+            .api
+            .call(HttpMethod::Get, &self.cluster_path(name), None)
+            .await?
+            .ok_or_else(|| missing("cluster"))?;
+        let endpoint = text(&cluster, &["endpoint"]).ok_or_else(|| missing("endpoint"))?;
+        let ca = text(&cluster, &["masterAuth", "clusterCaCertificate"]).ok_or_else(|| missing("masterAuth.clusterCaCertificate"))?;
+        Ok(kubeconfig_yaml(name, &format!("https://{}", endpoint), &ca, "gke-gcloud-auth-plugin", &[]))
+    }
+
+    async fn add_node_pool(&self, cluster: &str, pool: &NodePool) -> ContainerResult<Operation> {
+        let op = OperationRef::new(OperationKind::AddNodePool, cluster).with_pool(&pool.name);
+        let path = format!("{}/nodePools", self.cluster_path(cluster));
+        self.submit(op, HttpMethod::Post, path, Some(json!({ "nodePool": Self::node_pool(pool, None)? }))).await
+    }
+
+    async fn resize_node_pool(&self, cluster: &str, pool: &str, desired_size: i32) -> ContainerResult<Operation> {
+        let op = OperationRef::new(OperationKind::ResizeNodePool, cluster).with_pool(pool);
+        let path = format!("{}:setSize", self.pool_path(cluster, pool));
+        self.submit(op, HttpMethod::Post, path, Some(json!({ "nodeCount": desired_size }))).await
+    }
+
+    async fn delete_node_pool(&self, cluster: &str, pool: &str) -> ContainerResult<Operation> {
+        let op = OperationRef::new(OperationKind::DeleteNodePool, cluster).with_pool(pool);
+        self.submit(op, HttpMethod::Delete, self.pool_path(cluster, pool), None).await
+    }
+
+    async fn upgrade_control_plane(&self, cluster: &str, version: &str) -> ContainerResult<Operation> {
+        let op = OperationRef::new(OperationKind::UpgradeControlPlane, cluster);
+        let body = json!({ "update": { "desiredMasterVersion": version } });
+        self.submit(op, HttpMethod::Put, self.cluster_path(cluster), Some(body)).await
+    }
+
+    async fn upgrade_node_pool(&self, cluster: &str, pool: &str, version: &str, surge: &SurgeSettings) -> ContainerResult<Operation> {
+        let op = OperationRef::new(OperationKind::UpgradeNodePool, cluster).with_pool(pool);
+        let body = json!({ "nodeVersion": version, "upgradeSettings": Self::upgrade_settings(surge)? });
+        self.submit(op, HttpMethod::Put, self.pool_path(cluster, pool), Some(body)).await
+    }
+
+    async fn delete_cluster(&self, name: &str) -> ContainerResult<Operation> {
+        let op = OperationRef::new(OperationKind::DeleteCluster, name);
+        self.submit(op, HttpMethod::Delete, self.cluster_path(name), None).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use sirsi_key_vault::secret::InMemorySecretManager;
+
+    use super::*;
+    use crate::platform::cluster::testing::ScriptedApi;
+    use crate::platform::cluster::ClusterManager;
+
+    const CLUSTER: &str = "/v1/projects/acme/locations/us-central1/clusters/prod";
+
+    fn cluster(master: &str, pools: &[(&str, &str, &str)]) -> Option<Value> {
+        let pools: Vec<Value> = pools
+            .iter()
+            .map(|(name, version, status)| json!({ "name": name, "version": version, "status": status, "initialNodeCount": 3 }))
+            .collect();
+        Some(json!({ "status": "RUNNING", "currentMasterVersion": master, "endpoint": "34.1.2.3", "nodePools": pools }))
+    }
+
+    fn manager(api: Arc<ScriptedApi>) -> ClusterManager {
+        let target = GkeTarget { project: "acme".into(), location: "us-central1".into() };
+        ClusterManager::new(Arc::new(GkeProvisioner::new(api, target)), Arc::new(InMemorySecretManager::new()))
+            .with_poll_interval(Duration::from_millis(1))
+    }
+
+    #[tokio::test]
+    async fn test_upgrade_preconditions() {
+        let api = Arc::new(ScriptedApi::default());
+        api.respond(HttpMethod::Get, CLUSTER, vec![cluster("1.28.9-gke.1000", &[("default", "1.27.14-gke.100", "RUNNING")])]);
+        let manager = manager(api.clone());
+
+        // Skipping a minor, going backwards, or outrunning the control plane are all refused
+        assert!(manager.upgrade_control_plane("prod", "1.30").await.unwrap_err().to_string().contains("before 1.30"));
+        assert!(manager.upgrade_control_plane("prod", "1.27").await.is_err());
+        let surge = SurgeSettings::default();
+        assert!(manager.upgrade_node_pool("prod", "default", "1.29", &surge).await.unwrap_err().to_string().contains("ahead of"));
+        let percent = SurgeSettings { max_surge: Surge::Percent(25), max_unavailable: Surge::Nodes(0) };
+        assert!(manager.upgrade_node_pool("prod", "default", "1.28", &percent).await.is_err());
+        let nothing = SurgeSettings { max_surge: Surge::Nodes(0), max_unavailable: Surge::Nodes(0) };
+        assert!(manager.upgrade_node_pool("prod", "default", "1.28", &nothing).await.is_err());
+        assert!(api.calls_to(HttpMethod::Put, CLUSTER).is_empty());
+
+        let pool_path = format!("{}/nodePools/default", CLUSTER);
+        api.respond(HttpMethod::Put, &pool_path, vec![Some(json!({ "name": "operation-42" }))]);
+        let surge = SurgeSettings { max_surge: Surge::Nodes(2), max_unavailable: Surge::Nodes(1) };
+        let op = manager.upgrade_node_pool("prod", "default", "1.28", &surge).await.unwrap();
+        assert_eq!(op.id, "upgrade_node_pool:prod:default:operation-42");
+        assert_eq!(
+            api.calls_to(HttpMethod::Put, &pool_path)[0].as_ref().unwrap()["upgradeSettings"],
+            json!({ "strategy": "SURGE", "maxSurge": 2, "maxUnavailable": 1 })
+        );
+
+        // A pool mid-upgrade blocks the control plane from moving on
+        api.respond(HttpMethod::Get, CLUSTER, vec![cluster("1.28.9-gke.1000", &[("default", "1.27.14-gke.100", "RECONCILING")])]);
+        assert!(manager.upgrade_control_plane("prod", "1.29").await.unwrap_err().to_string().contains("settle"));
+
+        api.respond(HttpMethod::Get, CLUSTER, vec![cluster("1.28.9-gke.1000", &[("default", "1.28.9-gke.1000", "RUNNING")])]);
+        api.respond(HttpMethod::Put, CLUSTER, vec![Some(json!({ "name": "operation-43" }))]);
+        let op = manager.upgrade_control_plane("prod", "1.29").await.unwrap();
+        assert_eq!(api.calls_to(HttpMethod::Put, CLUSTER)[0], Some(json!({ "update": { "desiredMasterVersion": "1.29" } })));
+
+        api.respond(
+            HttpMethod::Get,
+            "/v1/projects/acme/locations/us-central1/operations/operation-43",
+            vec![Some(json!({ "status": "RUNNING" })), Some(json!({ "status": "DONE" }))],
+        );
+        let done = manager.wait(&op, Duration::from_secs(1)).await.unwrap();
+        assert_eq!(done.status, OperationStatus::Succeeded);
+        assert_eq!(done.kind, OperationKind::UpgradeControlPlane);
+    }
+}
@@ -0,0 +1,978 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sirsi_key_vault::secret::{Secret, SecretManager, SecretValue};
+use sirsi_key_vault::KeyVaultError;
+use tracing::{info, warn};
+
+use crate::error::{ContainerError, ContainerResult};
+
+pub mod aks;
+pub mod eks;
+pub mod gke;
+
+pub use aks::{AksProvisioner, AksTarget};
+pub use eks::{EksProvisioner, EksTarget};
+pub use gke::{GkeProvisioner, GkeTarget};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CloudProvider {
+    Eks,
+    Gke,
+    Aks,
+}
+
+impl CloudProvider {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CloudProvider::Eks => "eks",
+            CloudProvider::Gke => "gke",
+            CloudProvider::Aks => "aks",
+        }
+    }
+}
+
+// A Kubernetes minor release; patch levels are left to the provider
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct KubeVersion {
+    pub major: u32,
+    pub minor: u32,
+}
+
+impl KubeVersion {
+    // Accepts "1.29", "1.29.3", "v1.29.3-gke.1200" and the like
+    pub fn parse(version: &str) -> ContainerResult<Self> {
+        let invalid = || ContainerError::Validation(format!("Invalid Kubernetes version '{}'", version));
+        let mut parts = version.trim_start_matches('v').split(['.', '-', '+']);
+        let major = parts.next().and_then(|p| p.parse().ok()).ok_or_else(invalid)?;
+        let minor = parts.next().and_then(|p| p.parse().ok()).ok_or_else(invalid)?;
+        Ok(Self { major, minor })
+    }
+}
+
+impl fmt::Display for KubeVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+// A cluster node pool, sized the same way as a compute-manager `InstanceGroup`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodePool {
+    pub name: String,
+    pub instance_type: String,
+    pub min_size: i32,
+    pub max_size: i32,
+    pub desired_size: i32,
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+    pub root_volume_size: Option<i32>,
+    #[serde(default)]
+    pub surge: SurgeSettings,
+}
+
+impl NodePool {
+    pub fn new(name: impl Into<String>, instance_type: impl Into<String>, min_size: i32, max_size: i32) -> Self {
+        Self {
+            name: name.into(),
+            instance_type: instance_type.into(),
+            min_size,
+            max_size,
+            desired_size: min_size,
+            labels: HashMap::new(),
+            root_volume_size: None,
+            surge: SurgeSettings::default(),
+        }
+    }
+
+    pub fn with_desired_size(mut self, desired_size: i32) -> Self {
+        self.desired_size = desired_size;
+        self
+    }
+
+    pub fn with_label(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.labels.insert(key.into(), value.into());
+        self
+    }
+
+    fn validate(&self) -> ContainerResult<()> {
+        if self.name.is_empty() || self.instance_type.is_empty() {
+            return Err(ContainerError::Validation("Node pools need a name and an instance type".into()));
+        }
+        if self.min_size < 0 || self.min_size > self.max_size || !(self.min_size..=self.max_size).contains(&self.desired_size) {
+            return Err(ContainerError::Validation(format!(
+                "Node pool {} needs 0 <= min ({}) <= desired ({}) <= max ({})",
+                self.name, self.min_size, self.desired_size, self.max_size
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Surge {
+    Nodes(u32),
+    Percent(u32),
+}
+
+impl Surge {
+    fn is_zero(&self) -> bool {
+        matches!(self, Surge::Nodes(0) | Surge::Percent(0))
+    }
+}
+
+// How many extra nodes an upgrade may add, and how many may be drained at once
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SurgeSettings {
+    pub max_surge: Surge,
+    pub max_unavailable: Surge,
+}
+
+impl Default for SurgeSettings {
+    fn default() -> Self {
+        Self { max_surge: Surge::Nodes(1), max_unavailable: Surge::Nodes(0) }
+    }
+}
+
+impl SurgeSettings {
+    fn validate(&self) -> ContainerResult<()> {
+        if self.max_surge.is_zero() && self.max_unavailable.is_zero() {
+            return Err(ContainerError::Validation("max_surge and max_unavailable cannot both be zero".into()));
+        }
+        if [self.max_surge, self.max_unavailable].iter().any(|s| matches!(s, Surge::Percent(p) if *p > 100)) {
+            return Err(ContainerError::Validation("Surge percentages cannot exceed 100".into()));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ClusterNetwork {
+    // VPC, VNet or GCE network, in the provider's own form
+    pub network_id: Option<String>,
+    pub subnet_ids: Vec<String>,
+    pub pod_cidr: Option<String>,
+    pub service_cidr: Option<String>,
+    pub private_endpoint: bool,
+    // CIDRs allowed to reach a public API server; empty means unrestricted
+    pub authorized_cidrs: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SubjectKind {
+    User,
+    Group,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RbacBinding {
+    pub name: String,
+    pub cluster_role: String,
+    pub kind: SubjectKind,
+    pub subjects: Vec<String>,
+}
+
+// Applied once the cluster is ready; admin groups get `cluster-admin`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RbacBootstrap {
+    pub admin_groups: Vec<String>,
+    pub bindings: Vec<RbacBinding>,
+}
+
+impl RbacBootstrap {
+    pub fn is_empty(&self) -> bool {
+        self.admin_groups.is_empty() && self.bindings.is_empty()
+    }
+
+    // Every binding to apply, admin groups included
+    pub fn all_bindings(&self) -> Vec<RbacBinding> {
+        let mut bindings = self.bindings.clone();
+        if !self.admin_groups.is_empty() {
+            bindings.push(RbacBinding {
+                name: "sirsi-cluster-admins".to_string(),
+                cluster_role: "cluster-admin".to_string(),
+                kind: SubjectKind::Group,
+                subjects: self.admin_groups.clone(),
+            });
+        }
+        bindings
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClusterConfig {
+    pub name: String,
+    pub version: String,
+    pub node_pools: Vec<NodePool>,
+    #[serde(default)]
+    pub network: ClusterNetwork,
+    #[serde(default)]
+    pub rbac: RbacBootstrap,
+    #[serde(default)]
+    pub tags: HashMap<String, String>,
+}
+
+impl ClusterConfig {
+    fn validate(&self) -> ContainerResult<()> {
+        let name_ok = !self.name.is_empty()
+            && self.name.len() <= 40
+            && self.name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+            && !self.name.starts_with('-');
+        if !name_ok {
+            return Err(ContainerError::Validation(format!(
+                "Cluster name '{}' must be 1-40 lowercase letters, digits or dashes",
+                self.name
+            )));
+        }
+        KubeVersion::parse(&self.version)?;
+        if self.node_pools.is_empty() {
+            return Err(ContainerError::Validation("A cluster needs at least one node pool".into()));
+        }
+        let mut names = HashSet::new();
+        for pool in &self.node_pools {
+            pool.validate()?;
+            if !names.insert(pool.name.as_str()) {
+                return Err(ContainerError::Validation(format!("Node pool {} is listed twice", pool.name)));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClusterStatus {
+    Provisioning,
+    Running,
+    Updating,
+    Deleting,
+    Failed,
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodePoolInfo {
+    pub name: String,
+    pub version: Option<String>,
+    pub instance_type: Option<String>,
+    pub min_size: Option<i32>,
+    pub max_size: Option<i32>,
+    pub desired_size: Option<i32>,
+    pub status: ClusterStatus,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClusterInfo {
+    pub name: String,
+    pub provider: CloudProvider,
+    pub version: String,
+    pub status: ClusterStatus,
+    // The provider's own reason when `status` is failed
+    pub status_message: Option<String>,
+    pub endpoint: Option<String>,
+    pub node_pools: Vec<NodePoolInfo>,
+}
+
+impl ClusterInfo {
+    pub fn node_pool(&self, name: &str) -> Option<&NodePoolInfo> {
+        self.node_pools.iter().find(|p| p.name == name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OperationKind {
+    CreateCluster,
+    DeleteCluster,
+    UpgradeControlPlane,
+    AddNodePool,
+    ResizeNodePool,
+    UpgradeNodePool,
+    DeleteNodePool,
+}
+
+impl OperationKind {
+    const ALL: [OperationKind; 7] = [
+        OperationKind::CreateCluster,
+        OperationKind::DeleteCluster,
+        OperationKind::UpgradeControlPlane,
+        OperationKind::AddNodePool,
+        OperationKind::ResizeNodePool,
+        OperationKind::UpgradeNodePool,
+        OperationKind::DeleteNodePool,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OperationKind::CreateCluster => "create_cluster",
+            OperationKind::DeleteCluster => "delete_cluster",
+            OperationKind::UpgradeControlPlane => "upgrade_control_plane",
+            OperationKind::AddNodePool => "add_node_pool",
+            OperationKind::ResizeNodePool => "resize_node_pool",
+            OperationKind::UpgradeNodePool => "upgrade_node_pool",
+            OperationKind::DeleteNodePool => "delete_node_pool",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", content = "message", rename_all = "lowercase")]
+pub enum OperationStatus {
+    Running,
+    Succeeded,
+    Failed(String),
+}
+
+// A long-running provider call; `id` is opaque and only meaningful to the provisioner that issued it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Operation {
+    pub id: String,
+    pub kind: OperationKind,
+    pub cluster: String,
+    pub status: OperationStatus,
+}
+
+impl Operation {
+    pub fn is_done(&self) -> bool {
+        !matches!(self.status, OperationStatus::Running)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HttpMethod {
+    Get,
+    Post,
+    Put,
+    Patch,
+    Delete,
+}
+
+// Authenticated JSON calls against a provider's management API; request signing (SigV4,
+// OAuth, AAD) is the transport's job
+#[async_trait]
+pub trait CloudApi: Send + Sync {
+    // `None` when the resource does not exist
+    async fn call(&self, method: HttpMethod, path: &str, body: Option<Value>) -> ContainerResult<Option<Value>>;
+}
+
+// Creates and manages clusters on one managed Kubernetes service
+#[async_trait]
+pub trait ClusterProvisioner: Send + Sync {
+    fn provider(&self) -> CloudProvider;
+    async fn create_cluster(&self, config: &ClusterConfig) -> ContainerResult<Operation>;
+    async fn describe_cluster(&self, name: &str) -> ContainerResult<Option<ClusterInfo>>;
+    async fn operation(&self, id: &str) -> ContainerResult<Operation>;
+    async fn kubeconfig(&self, name: &str) -> ContainerResult<String>;
+    async fn add_node_pool(&self, cluster: &str, pool: &NodePool) -> ContainerResult<Operation>;
+    async fn resize_node_pool(&self, cluster: &str, pool: &str, desired_size: i32) -> ContainerResult<Operation>;
+    async fn delete_node_pool(&self, cluster: &str, pool: &str) -> ContainerResult<Operation>;
+    async fn upgrade_control_plane(&self, cluster: &str, version: &str) -> ContainerResult<Operation>;
+    async fn upgrade_node_pool(&self, cluster: &str, pool: &str, version: &str, surge: &SurgeSettings) -> ContainerResult<Operation>;
+    async fn delete_cluster(&self, name: &str) -> ContainerResult<Operation>;
+}
+
+// In-cluster calls made with the stored kubeconfig
+#[async_trait]
+pub trait ClusterAccess: Send + Sync {
+    async fn apply_rbac(&self, kubeconfig: &str, bindings: &[RbacBinding]) -> ContainerResult<()>;
+    // Workloads outside the system namespaces, as `namespace/kind/name`
+    async fn user_workloads(&self, kubeconfig: &str) -> ContainerResult<Vec<String>>;
+}
+
+// Where in-cluster operations get credentials for a cluster by name
+#[async_trait]
+pub trait KubeconfigSource: Send + Sync {
+    async fn kubeconfig(&self, cluster: &str) -> ContainerResult<String>;
+}
+
+#[async_trait]
+impl KubeconfigSource for ClusterManager {
+    async fn kubeconfig(&self, cluster: &str) -> ContainerResult<String> {
+        ClusterManager::kubeconfig(self, cluster).await
+    }
+}
+
+// Fixed kubeconfigs by cluster name, for clusters SirsiNexus did not provision
+#[async_trait]
+impl KubeconfigSource for HashMap<String, String> {
+    async fn kubeconfig(&self, cluster: &str) -> ContainerResult<String> {
+        self.get(cluster)
+            .cloned()
+            .ok_or_else(|| ContainerError::NotFound(format!("No kubeconfig for cluster {}", cluster)))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Readiness {
+    Pending(String),
+    Ready,
+    Failed(String),
+}
+
+// Folds successive `describe_cluster` results into a verdict. A cluster that has been seen
+// and then vanishes has failed, but one not yet visible is just eventual consistency.
+#[derive(Debug, Clone)]
+pub struct ReadinessTracker {
+    expected_pools: Vec<String>,
+    seen: bool,
+}
+
+impl ReadinessTracker {
+    pub fn new(expected_pools: impl IntoIterator<Item = String>) -> Self {
+        Self { expected_pools: expected_pools.into_iter().collect(), seen: false }
+    }
+
+    pub fn observe(&mut self, info: Option<&ClusterInfo>) -> Readiness {
+        let Some(info) = info else {
+            return if self.seen {
+                Readiness::Failed("cluster disappeared while provisioning".into())
+            } else {
+                Readiness::Pending("cluster not visible yet".into())
+            };
+        };
+        self.seen = true;
+        match info.status {
+            ClusterStatus::Failed => {
+                return Readiness::Failed(info.status_message.clone().unwrap_or_else(|| "provider reported failure".into()))
+            }
+            ClusterStatus::Deleting => return Readiness::Failed("cluster is being deleted".into()),
+            ClusterStatus::Running => {}
+            status => return Readiness::Pending(format!("control plane is {:?}", status).to_lowercase()),
+        }
+        if let Some(failed) = info.node_pools.iter().find(|p| p.status == ClusterStatus::Failed) {
+            return Readiness::Failed(format!("node pool {} failed", failed.name));
+        }
+        let waiting: Vec<&str> = self
+            .expected_pools
+            .iter()
+            .filter(|name| info.node_pool(name).is_none_or(|p| p.status != ClusterStatus::Running))
+            .map(String::as_str)
+            .collect();
+        if !waiting.is_empty() {
+            return Readiness::Pending(format!("waiting for node pools: {}", waiting.join(", ")));
+        }
+        if info.endpoint.is_none() {
+            return Readiness::Pending("API endpoint not published yet".into());
+        }
+        Readiness::Ready
+    }
+}
+
+fn vault_error(secret_id: &str, e: KeyVaultError) -> ContainerError {
+    match e {
+        KeyVaultError::Permission(msg) => ContainerError::Permission(msg),
+        e => ContainerError::Internal(format!("Failed to access kubeconfig secret {}: {}", secret_id, e)),
+    }
+}
+
+// Provisions clusters through a provisioner, keeping each cluster's kubeconfig in the key vault
+pub struct ClusterManager {
+    provisioner: Arc<dyn ClusterProvisioner>,
+    secrets: Arc<dyn SecretManager>,
+    access: Option<Arc<dyn ClusterAccess>>,
+    poll_interval: Duration,
+    ready_timeout: Duration,
+}
+
+impl ClusterManager {
+    pub fn new(provisioner: Arc<dyn ClusterProvisioner>, secrets: Arc<dyn SecretManager>) -> Self {
+        Self {
+            provisioner,
+            secrets,
+            access: None,
+            poll_interval: Duration::from_secs(15),
+            ready_timeout: Duration::from_secs(45 * 60),
+        }
+    }
+
+    // Needed for RBAC bootstrap and for the empty check before an unforced delete
+    pub fn with_access(mut self, access: Arc<dyn ClusterAccess>) -> Self {
+        self.access = Some(access);
+        self
+    }
+
+    pub fn with_poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    pub fn with_ready_timeout(mut self, timeout: Duration) -> Self {
+        self.ready_timeout = timeout;
+        self
+    }
+
+    pub fn kubeconfig_secret_id(&self, cluster: &str) -> String {
+        format!("kubeconfig/{}/{}", self.provisioner.provider().as_str(), cluster)
+    }
+
+    pub async fn create_cluster(&self, config: &ClusterConfig) -> ContainerResult<Operation> {
+        config.validate()?;
+        if self.provisioner.describe_cluster(&config.name).await?.is_some() {
+            return Err(ContainerError::Validation(format!("Cluster {} already exists", config.name)));
+        }
+        let operation = self.provisioner.create_cluster(config).await?;
+        info!(cluster = %config.name, provider = self.provisioner.provider().as_str(), operation = %operation.id, "Creating cluster");
+        Ok(operation)
+    }
+
+    // Creates the cluster and returns once it is ready, credentials stored and RBAC applied
+    pub async fn provision(&self, config: &ClusterConfig) -> ContainerResult<ClusterInfo> {
+        self.create_cluster(config).await?;
+        self.wait_until_ready(config).await
+    }
+
+    // Polls until the control plane and every configured node pool are running. Pools the
+    // provider did not create with the control plane are added once it is up.
+    pub async fn wait_until_ready(&self, config: &ClusterConfig) -> ContainerResult<ClusterInfo> {
+        let mut tracker = ReadinessTracker::new(config.node_pools.iter().map(|p| p.name.clone()));
+        let mut requested = HashSet::new();
+        let deadline = tokio::time::Instant::now() + self.ready_timeout;
+        let info = loop {
+            let info = self.provisioner.describe_cluster(&config.name).await?;
+            if let Some(info) = info.as_ref().filter(|i| i.status == ClusterStatus::Running) {
+                for pool in &config.node_pools {
+                    if info.node_pool(&pool.name).is_none() && requested.insert(pool.name.clone()) {
+                        self.provisioner.add_node_pool(&config.name, pool).await?;
+                    }
+                }
+            }
+            match tracker.observe(info.as_ref()) {
+                Readiness::Ready => break info.expect("ready clusters are described"),
+                Readiness::Failed(reason) => {
+                    return Err(ContainerError::Platform(format!("Cluster {} failed to provision: {}", config.name, reason)))
+                }
+                Readiness::Pending(reason) => {
+                    if tokio::time::Instant::now() >= deadline {
+                        return Err(ContainerError::Platform(format!(
+                            "Cluster {} not ready within {:?}: {}",
+                            config.name, self.ready_timeout, reason
+                        )));
+                    }
+                }
+            }
+            tokio::time::sleep(self.poll_interval).await;
+        };
+
+        let kubeconfig = self.provisioner.kubeconfig(&config.name).await?;
+        self.store_kubeconfig(&config.name, &kubeconfig).await?;
+        if !config.rbac.is_empty() {
+            match &self.access {
+                Some(access) => access.apply_rbac(&kubeconfig, &config.rbac.all_bindings()).await?,
+                None => warn!(cluster = %config.name, "No cluster access configured; skipping RBAC bootstrap"),
+            }
+        }
+        info!(cluster = %config.name, version = %info.version, "Cluster ready");
+        Ok(info)
+    }
+
+    pub async fn wait(&self, operation: &Operation, timeout: Duration) -> ContainerResult<Operation> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let current = self.provisioner.operation(&operation.id).await?;
+            if current.is_done() {
+                return Ok(current);
+            }
+            if tokio::time::Instant::now() >= deadline {
+                return Err(ContainerError::Platform(format!("Operation {} still running after {:?}", operation.id, timeout)));
+            }
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+
+    pub async fn kubeconfig(&self, cluster: &str) -> ContainerResult<String> {
+        let secret_id = self.kubeconfig_secret_id(cluster);
+        let secret = self.secrets.get_secret(&secret_id).await.map_err(|e| match e {
+            KeyVaultError::NotFound(_) => ContainerError::NotFound(format!("No kubeconfig stored for cluster {}", cluster)),
+            e => vault_error(&secret_id, e),
+        })?;
+        match secret.value {
+            SecretValue::Plain(kubeconfig) => Ok(kubeconfig),
+            _ => Err(ContainerError::Internal(format!("Secret {} is not a kubeconfig", secret_id))),
+        }
+    }
+
+    async fn store_kubeconfig(&self, cluster: &str, kubeconfig: &str) -> ContainerResult<i32> {
+        let secret_id = self.kubeconfig_secret_id(cluster);
+        let now = Utc::now();
+        let secret = Secret {
+            id: secret_id.clone(),
+            name: format!("{} kubeconfig", cluster),
+            description: Some("Admin kubeconfig for a provisioned Kubernetes cluster".to_string()),
+            value: SecretValue::Plain(kubeconfig.to_string()),
+            version: 0,
+            created_at: now,
+            updated_at: now,
+            expires_at: None,
+            metadata: HashMap::from([
+                ("cluster".to_string(), cluster.to_string()),
+                ("provider".to_string(), self.provisioner.provider().as_str().to_string()),
+            ]),
+            labels: HashMap::from([("kind".to_string(), "kubeconfig".to_string())]),
+            rotation_policy: None,
+        };
+        let stored = match self.secrets.get_secret(&secret_id).await {
+            Ok(_) => self.secrets.update_secret(secret).await,
+            Err(KeyVaultError::NotFound(_)) => self.secrets.create_secret(secret).await,
+            Err(e) => return Err(vault_error(&secret_id, e)),
+        };
+        stored.map(|s| s.version).map_err(|e| vault_error(&secret_id, e))
+    }
+
+    async fn running_cluster(&self, name: &str) -> ContainerResult<ClusterInfo> {
+        let info = self
+            .provisioner
+            .describe_cluster(name)
+            .await?
+            .ok_or_else(|| ContainerError::NotFound(format!("Cluster {} not found", name)))?;
+        if info.status != ClusterStatus::Running {
+            return Err(ContainerError::Validation(format!(
+                "Cluster {} is {:?}; wait for it to be running",
+                name, info.status
+            )));
+        }
+        Ok(info)
+    }
+
+    pub async fn add_node_pool(&self, cluster: &str, pool: &NodePool) -> ContainerResult<Operation> {
+        pool.validate()?;
+        let info = self.running_cluster(cluster).await?;
+        if info.node_pool(&pool.name).is_some() {
+            return Err(ContainerError::Validation(format!("Node pool {} already exists in {}", pool.name, cluster)));
+        }
+        self.provisioner.add_node_pool(cluster, pool).await
+    }
+
+    pub async fn resize_node_pool(&self, cluster: &str, pool: &str, desired_size: i32) -> ContainerResult<Operation> {
+        let info = self.running_cluster(cluster).await?;
+        let current = info
+            .node_pool(pool)
+            .ok_or_else(|| ContainerError::NotFound(format!("Node pool {} not found in {}", pool, cluster)))?;
+        let (min, max) = (current.min_size.unwrap_or(0), current.max_size.unwrap_or(i32::MAX));
+        if !(min..=max).contains(&desired_size) {
+            return Err(ContainerError::Validation(format!(
+                "Node pool {} must stay between {} and {} nodes",
+                pool, min, max
+            )));
+        }
+        self.provisioner.resize_node_pool(cluster, pool, desired_size).await
+    }
+
+    pub async fn delete_node_pool(&self, cluster: &str, pool: &str) -> ContainerResult<Operation> {
+        let info = self.running_cluster(cluster).await?;
+        if info.node_pool(pool).is_none() {
+            return Err(ContainerError::NotFound(format!("Node pool {} not found in {}", pool, cluster)));
+        }
+        if info.node_pools.len() == 1 {
+            return Err(ContainerError::Validation(format!(
+                "{} is the last node pool in {}; delete the cluster instead",
+                pool, cluster
+            )));
+        }
+        self.provisioner.delete_node_pool(cluster, pool).await
+    }
+
+    // Control planes move one minor version at a time and never backwards
+    pub async fn upgrade_control_plane(&self, cluster: &str, version: &str) -> ContainerResult<Operation> {
+        let target = KubeVersion::parse(version)?;
+        let info = self.running_cluster(cluster).await?;
+        let current = KubeVersion::parse(&info.version)?;
+        if target.major != current.major || target.minor < current.minor {
+            return Err(ContainerError::Validation(format!("Cannot move {} from {} to {}", cluster, current, target)));
+        }
+        if target.minor > current.minor + 1 {
+            return Err(ContainerError::Validation(format!(
+                "Upgrade {} to {}.{} before {}",
+                cluster,
+                current.major,
+                current.minor + 1,
+                target
+            )));
+        }
+        if let Some(busy) = info.node_pools.iter().find(|p| p.status != ClusterStatus::Running) {
+            return Err(ContainerError::Validation(format!("Node pool {} is {:?}; wait for it to settle", busy.name, busy.status)));
+        }
+        self.provisioner.upgrade_control_plane(cluster, version).await
+    }
+
+    // Node pools can catch up to the control plane but never pass it
+    pub async fn upgrade_node_pool(
+        &self,
+        cluster: &str,
+        pool: &str,
+        version: &str,
+        surge: &SurgeSettings,
+    ) -> ContainerResult<Operation> {
+        surge.validate()?;
+        let target = KubeVersion::parse(version)?;
+        let info = self.running_cluster(cluster).await?;
+        let control_plane = KubeVersion::parse(&info.version)?;
+        if target > control_plane {
+            return Err(ContainerError::Validation(format!(
+                "Node pool {} cannot run {} ahead of the {} control plane",
+                pool, target, control_plane
+            )));
+        }
+        let current = info
+            .node_pool(pool)
+            .ok_or_else(|| ContainerError::NotFound(format!("Node pool {} not found in {}", pool, cluster)))?;
+        if current.status != ClusterStatus::Running {
+            return Err(ContainerError::Validation(format!("Node pool {} is {:?}; wait for it to settle", pool, current.status)));
+        }
+        if let Some(current) = current.version.as_deref().map(KubeVersion::parse).transpose()? {
+            if target < current {
+                return Err(ContainerError::Validation(format!("Node pool {} cannot be downgraded from {}", pool, current)));
+            }
+        }
+        self.provisioner.upgrade_node_pool(cluster, pool, version, surge).await
+    }
+
+    // Refuses while user workloads remain unless `force` is set; the stored kubeconfig goes with the cluster
+    pub async fn delete_cluster(&self, name: &str, force: bool) -> ContainerResult<Operation> {
+        if self.provisioner.describe_cluster(name).await?.is_none() {
+            return Err(ContainerError::NotFound(format!("Cluster {} not found", name)));
+        }
+        if !force {
+            let access = self.access.as_ref().ok_or_else(|| {
+                ContainerError::Config("Cannot verify the cluster is empty without cluster access; pass force to delete anyway".into())
+            })?;
+            let workloads = access.user_workloads(&self.kubeconfig(name).await?).await?;
+            if !workloads.is_empty() {
+                let shown: Vec<&str> = workloads.iter().take(5).map(String::as_str).collect();
+                return Err(ContainerError::Validation(format!(
+                    "Cluster {} still runs {} workloads ({}); remove them or force the delete",
+                    name,
+                    workloads.len(),
+                    shown.join(", ")
+                )));
+            }
+        }
+        let operation = self.provisioner.delete_cluster(name).await?;
+        let secret_id = self.kubeconfig_secret_id(name);
+        match self.secrets.delete_secret(&secret_id).await {
+            Ok(()) | Err(KeyVaultError::NotFound(_)) => {}
+            Err(e) => warn!(cluster = %name, "Failed to remove kubeconfig secret: {}", e),
+        }
+        info!(cluster = %name, force, operation = %operation.id, "Deleting cluster");
+        Ok(operation)
+    }
+}
+
+// What an operation id encodes: `kind:cluster:pool:token`, where the token is the provider's
+// own operation or update id when it has one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct OperationRef {
+    pub kind: OperationKind,
+    pub cluster: String,
+    pub pool: Option<String>,
+    pub token: Option<String>,
+}
+
+impl OperationRef {
+    pub fn new(kind: OperationKind, cluster: &str) -> Self {
+        Self { kind, cluster: cluster.to_string(), pool: None, token: None }
+    }
+
+    pub fn with_pool(mut self, pool: &str) -> Self {
+        self.pool = Some(pool.to_string());
+        self
+    }
+
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    pub fn id(&self) -> String {
+        format!(
+            "{}:{}:{}:{}",
+            self.kind.as_str(),
+            self.cluster,
+            self.pool.as_deref().unwrap_or_default(),
+            self.token.as_deref().unwrap_or_default()
+        )
+    }
+
+    pub fn parse(id: &str) -> ContainerResult<Self> {
+        let invalid = || ContainerError::Validation(format!("Unknown operation id '{}'", id));
+        let mut parts = id.splitn(4, ':');
+        let kind = parts.next().and_then(|k| OperationKind::ALL.into_iter().find(|o| o.as_str() == k)).ok_or_else(invalid)?;
+        let cluster = parts.next().filter(|c| !c.is_empty()).ok_or_else(invalid)?.to_string();
+        let non_empty = |p: Option<&str>| p.filter(|p| !p.is_empty()).map(str::to_string);
+        Ok(Self { kind, cluster, pool: non_empty(parts.next()), token: non_empty(parts.next()) })
+    }
+
+    pub fn status(&self, status: OperationStatus) -> Operation {
+        Operation { id: self.id(), kind: self.kind, cluster: self.cluster.clone(), status }
+    }
+
+    pub fn pool(&self) -> ContainerResult<&str> {
+        self.pool.as_deref().ok_or_else(|| ContainerError::Validation(format!("Operation {} names no node pool", self.id())))
+    }
+}
+
+// Reads a string field a few objects deep, e.g. `text(&v, &["cluster", "status"])`
+pub(crate) fn text(value: &Value, path: &[&str]) -> Option<String> {
+    path.iter().try_fold(value, |v, key| v.get(key))?.as_str().map(str::to_string)
+}
+
+pub(crate) fn int(value: &Value, path: &[&str]) -> Option<i32> {
+    path.iter().try_fold(value, |v, key| v.get(key))?.as_i64().map(|n| n as i32)
+}
+
+pub(crate) fn missing(what: &str) -> ContainerError {
+    ContainerError::Platform(format!("Provider response is missing {}", what))
+}
+
+pub(crate) fn kubeconfig_yaml(cluster: &str, endpoint: &str, ca_data: &str, command: &str, args: &[&str]) -> String {
+    let args: String = args.iter().map(|a| format!("\n      - {}", a)).collect();
+    format!(
+        "apiVersion: v1\nkind: Config\nclusters:\n- name: {cluster}\n  cluster:\n    server: {endpoint}\n    certificate-authority-data: {ca_data}\ncontexts:\n- name: {cluster}\n  context:\n    cluster: {cluster}\n    user: {cluster}\ncurrent-context: {cluster}\nusers:\n- name: {cluster}\n  user:\n    exec:\n      apiVersion: client.authentication.k8s.io/v1beta1\n      command: {command}\n      args:{args}\n"
+    )
+}
+
+// Scripted provider API responses for the provisioner tests
+#[cfg(test)]
+pub(crate) mod testing {
+    use std::collections::{HashMap, VecDeque};
+    use std::sync::Mutex;
+
+    use super::*;
+
+    #[derive(Default)]
+    pub struct ScriptedApi {
+        responses: Mutex<HashMap<String, VecDeque<Option<Value>>>>,
+        pub calls: Mutex<Vec<(HttpMethod, String, Option<Value>)>>,
+    }
+
+    impl ScriptedApi {
+        // Answers are served in order and the last one repeats
+        pub fn respond(&self, method: HttpMethod, path: &str, answers: Vec<Option<Value>>) {
+            self.responses.lock().unwrap().insert(format!("{:?} {}", method, path), answers.into());
+        }
+
+        pub fn calls_to(&self, method: HttpMethod, path: &str) -> Vec<Option<Value>> {
+            self.calls
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|(m, p, _)| *m == method && p == path)
+                .map(|(_, _, body)| body.clone())
+                .collect()
+        }
+    }
+
+    #[async_trait]
+    impl CloudApi for ScriptedApi {
+        async fn call(&self, method: HttpMethod, path: &str, body: Option<Value>) -> ContainerResult<Option<Value>> {
+            self.calls.lock().unwrap().push((method, path.to_string(), body));
+            let mut responses = self.responses.lock().unwrap();
+            let answers = responses
+                .get_mut(&format!("{:?} {}", method, path))
+                .ok_or_else(|| ContainerError::Internal(format!("Unscripted call {:?} {}", method, path)))?;
+            Ok(if answers.len() > 1 { answers.pop_front().flatten() } else { answers.front().cloned().flatten() })
+        }
+    }
+
+    #[derive(Default)]
+    pub struct FakeAccess {
+        pub workloads: Mutex<Vec<String>>,
+        pub applied: Mutex<Vec<RbacBinding>>,
+    }
+
+    #[async_trait]
+    impl ClusterAccess for FakeAccess {
+        async fn apply_rbac(&self, _kubeconfig: &str, bindings: &[RbacBinding]) -> ContainerResult<()> {
+            self.applied.lock().unwrap().extend_from_slice(bindings);
+            Ok(())
+        }
+
+        async fn user_workloads(&self, _kubeconfig: &str) -> ContainerResult<Vec<String>> {
+            Ok(self.workloads.lock().unwrap().clone())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(status: ClusterStatus, pools: &[(&str, ClusterStatus)], endpoint: bool) -> ClusterInfo {
+        ClusterInfo {
+            name: "prod".into(),
+            provider: CloudProvider::Eks,
+            version: "1.29".into(),
+            status,
+            status_message: None,
+            endpoint: endpoint.then(|| "https://prod.example".to_string()),
+            node_pools: pools
+                .iter()
+                .map(|(name, status)| NodePoolInfo {
+                    name: name.to_string(),
+                    version: Some("1.29".into()),
+                    instance_type: None,
+                    min_size: None,
+                    max_size: None,
+                    desired_size: None,
+                    status: *status,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_readiness_tracker_transitions() {
+        use ClusterStatus::*;
+        let mut tracker = ReadinessTracker::new(["system".to_string(), "batch".to_string()]);
+        assert_eq!(tracker.observe(None), Readiness::Pending("cluster not visible yet".into()));
+        assert_eq!(tracker.observe(Some(&info(Provisioning, &[], false))), Readiness::Pending("control plane is provisioning".into()));
+        assert_eq!(
+            tracker.observe(Some(&info(Running, &[("system", Running), ("batch", Provisioning)], true))),
+            Readiness::Pending("waiting for node pools: batch".into())
+        );
+        assert_eq!(
+            tracker.observe(Some(&info(Running, &[("system", Running), ("batch", Running)], false))),
+            Readiness::Pending("API endpoint not published yet".into())
+        );
+        assert_eq!(tracker.observe(Some(&info(Running, &[("system", Running), ("batch", Running)], true))), Readiness::Ready);
+
+        assert!(matches!(tracker.observe(Some(&info(Running, &[("system", Running), ("batch", Failed)], true))), Readiness::Failed(_)));
+        let mut failed = info(Failed, &[], false);
+        failed.status_message = Some("subnet has no free addresses".into());
+        assert_eq!(tracker.observe(Some(&failed)), Readiness::Failed("subnet has no free addresses".into()));
+        // Once seen, a missing cluster is a failure rather than a delay
+        assert_eq!(tracker.observe(None), Readiness::Failed("cluster disappeared while provisioning".into()));
+    }
+
+    #[test]
+    fn test_config_and_version_validation() {
+        assert_eq!(KubeVersion::parse("v1.29.3-gke.1200").unwrap(), KubeVersion { major: 1, minor: 29 });
+        assert!(KubeVersion::parse("latest").is_err());
+
+        let mut config = ClusterConfig {
+            name: "prod-east".into(),
+            version: "1.29".into(),
+            node_pools: vec![NodePool::new("system", "m5.large", 2, 5).with_desired_size(3)],
+            network: ClusterNetwork::default(),
+            rbac: RbacBootstrap { admin_groups: vec!["platform".into()], bindings: Vec::new() },
+            tags: HashMap::new(),
+        };
+        assert!(config.validate().is_ok());
+        assert_eq!(config.rbac.all_bindings()[0].cluster_role, "cluster-admin");
+        config.node_pools.push(NodePool::new("system", "m5.large", 1, 1));
+        assert!(config.validate().is_err());
+        config.node_pools[1] = NodePool::new("batch", "m5.large", 3, 2);
+        assert!(config.validate().is_err());
+        config.node_pools.truncate(1);
+        config.name = "Prod_East".into();
+        assert!(config.validate().is_err());
+    }
+}
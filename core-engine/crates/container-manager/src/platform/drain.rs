@@ -0,0 +1,715 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Mutex, RwLock};
+use tracing::{info, warn};
+
+use super::cluster::KubeconfigSource;
+use crate::error::{ContainerError, ContainerResult};
+use crate::service::{MANAGED_BY_LABEL, MANAGED_BY_VALUE};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DrainOptions {
+    // How long PDB-blocked evictions are retried
+    pub timeout: Duration,
+    // At the deadline, delete pods that are still blocked, and delete pods no controller will recreate
+    pub force: bool,
+    pub grace_period_seconds: Option<i64>,
+    // Evict pods with emptyDir volumes, losing that data
+    pub delete_emptydir_data: bool,
+}
+
+impl Default for DrainOptions {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(300),
+            force: false,
+            grace_period_seconds: None,
+            delete_emptydir_data: false,
+        }
+    }
+}
+
+// A pod scheduled on the node being drained
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NodePod {
+    pub namespace: String,
+    pub name: String,
+    // Kind of the controlling owner, e.g. `ReplicaSet` or `DaemonSet`
+    pub controller_kind: Option<String>,
+    pub mirror: bool,
+    pub finished: bool,
+    pub emptydir: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EvictionResult {
+    Accepted,
+    // The API server refused the eviction, normally because a PDB would be violated
+    Blocked(String),
+    Gone,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WorkloadKind {
+    Deployment,
+    StatefulSet,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkloadRef {
+    pub namespace: String,
+    pub kind: WorkloadKind,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkloadInfo {
+    pub workload: WorkloadRef,
+    pub replicas: i32,
+    pub selector: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MinAvailable {
+    Pods(i32),
+    Percent(u32),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PdbSpec {
+    pub namespace: String,
+    pub name: String,
+    pub selector: BTreeMap<String, String>,
+    pub min_available: MinAvailable,
+}
+
+// The node, pod and policy calls a drain needs
+#[async_trait]
+pub trait DrainApi: Send + Sync {
+    async fn set_unschedulable(&self, kubeconfig: &str, node: &str, unschedulable: bool) -> ContainerResult<()>;
+    async fn node_pods(&self, kubeconfig: &str, node: &str) -> ContainerResult<Vec<NodePod>>;
+    async fn evict_pod(&self, kubeconfig: &str, namespace: &str, name: &str, grace_period_seconds: Option<i64>) -> ContainerResult<EvictionResult>;
+    async fn delete_pod(&self, kubeconfig: &str, namespace: &str, name: &str, grace_period_seconds: Option<i64>) -> ContainerResult<()>;
+    async fn workload(&self, kubeconfig: &str, workload: &WorkloadRef) -> ContainerResult<Option<WorkloadInfo>>;
+    async fn list_workloads(&self, kubeconfig: &str, namespace: &str, labels: &str) -> ContainerResult<Vec<WorkloadInfo>>;
+    async fn apply_pdb(&self, kubeconfig: &str, pdb: &PdbSpec) -> ContainerResult<()>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", content = "reason", rename_all = "snake_case")]
+pub enum EvictionStatus {
+    Pending,
+    Evicted,
+    // Deleted past its disruption budget at the deadline
+    Forced,
+    Skipped(String),
+    Refused(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PodEviction {
+    pub namespace: String,
+    pub name: String,
+    pub status: EvictionStatus,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DrainPhase {
+    Draining,
+    Completed,
+    Failed,
+    Aborted,
+}
+
+impl DrainPhase {
+    pub fn is_terminal(&self) -> bool {
+        !matches!(self, DrainPhase::Draining)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DrainRecord {
+    pub id: String,
+    pub cluster: String,
+    pub node: String,
+    pub options: DrainOptions,
+    pub phase: DrainPhase,
+    pub pods: Vec<PodEviction>,
+    pub error: Option<String>,
+    pub started_at: DateTime<Utc>,
+    // Fixed when the drain starts, so a resumed drain keeps the original deadline
+    pub deadline: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl DrainRecord {
+    fn pod_mut(&mut self, namespace: &str, name: &str) -> &mut PodEviction {
+        let index = match self.pods.iter().position(|p| p.namespace == namespace && p.name == name) {
+            Some(index) => index,
+            None => {
+                self.pods.push(PodEviction {
+                    namespace: namespace.to_string(),
+                    name: name.to_string(),
+                    status: EvictionStatus::Pending,
+                    attempts: 0,
+                    last_error: None,
+                });
+                self.pods.len() - 1
+            }
+        };
+        &mut self.pods[index]
+    }
+
+    fn finish(&mut self, phase: DrainPhase, error: Option<String>) {
+        self.phase = phase;
+        self.error = error;
+        self.updated_at = Utc::now();
+    }
+}
+
+// Persistence for drains so one interrupted by a restart can be resumed
+#[async_trait]
+pub trait DrainStore: Send + Sync {
+    async fn save_drain(&self, record: &DrainRecord) -> ContainerResult<()>;
+    async fn get_drain(&self, id: &str) -> ContainerResult<Option<DrainRecord>>;
+    async fn list_active_drains(&self) -> ContainerResult<Vec<DrainRecord>>;
+}
+
+#[derive(Default)]
+pub struct InMemoryDrainStore {
+    drains: RwLock<HashMap<String, DrainRecord>>,
+}
+
+impl InMemoryDrainStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl DrainStore for InMemoryDrainStore {
+    async fn save_drain(&self, record: &DrainRecord) -> ContainerResult<()> {
+        self.drains.write().await.insert(record.id.clone(), record.clone());
+        Ok(())
+    }
+
+    async fn get_drain(&self, id: &str) -> ContainerResult<Option<DrainRecord>> {
+        Ok(self.drains.read().await.get(id).cloned())
+    }
+
+    async fn list_active_drains(&self) -> ContainerResult<Vec<DrainRecord>> {
+        let mut records: Vec<DrainRecord> = self
+            .drains
+            .read()
+            .await
+            .values()
+            .filter(|r| !r.phase.is_terminal())
+            .cloned()
+            .collect();
+        records.sort_by_key(|r| r.started_at);
+        Ok(records)
+    }
+}
+
+// Why a pod is left alone or cannot be drained, as kubectl decides it
+fn classify(pod: &NodePod, options: &DrainOptions) -> Option<EvictionStatus> {
+    if pod.mirror {
+        return Some(EvictionStatus::Skipped("mirror pod; managed by the kubelet".into()));
+    }
+    if pod.controller_kind.as_deref() == Some("DaemonSet") {
+        return Some(EvictionStatus::Skipped("managed by a DaemonSet".into()));
+    }
+    if pod.finished {
+        return None;
+    }
+    if pod.controller_kind.is_none() && !options.force {
+        return Some(EvictionStatus::Refused("not managed by a controller; force deletes it".into()));
+    }
+    if pod.emptydir && !options.delete_emptydir_data {
+        return Some(EvictionStatus::Refused("uses emptyDir data that eviction would delete".into()));
+    }
+    None
+}
+
+// Cordons a node and evicts its pods through the eviction API, so PodDisruptionBudgets are
+// honoured. Blocked evictions are retried until the drain's deadline.
+pub struct NodeDrainer {
+    api: Arc<dyn DrainApi>,
+    kubeconfigs: Arc<dyn KubeconfigSource>,
+    store: Arc<dyn DrainStore>,
+    retry_interval: Duration,
+    // Serialises saves against aborts, so a running drain cannot overwrite an abort
+    saving: Mutex<()>,
+}
+
+impl NodeDrainer {
+    pub fn new(api: Arc<dyn DrainApi>, kubeconfigs: Arc<dyn KubeconfigSource>) -> Self {
+        Self {
+            api,
+            kubeconfigs,
+            store: Arc::new(InMemoryDrainStore::new()),
+            retry_interval: Duration::from_secs(5),
+            saving: Mutex::new(()),
+        }
+    }
+
+    pub fn with_store(mut self, store: Arc<dyn DrainStore>) -> Self {
+        self.store = store;
+        self
+    }
+
+    pub fn with_retry_interval(mut self, interval: Duration) -> Self {
+        self.retry_interval = interval;
+        self
+    }
+
+    pub async fn drain_node(&self, cluster: &str, node: &str, options: DrainOptions) -> ContainerResult<DrainRecord> {
+        let active = self.store.list_active_drains().await?;
+        if let Some(existing) = active.iter().find(|r| r.cluster == cluster && r.node == node) {
+            return Err(ContainerError::Validation(format!("Node {} is already being drained by {}", node, existing.id)));
+        }
+        let now = Utc::now();
+        let timeout = chrono::Duration::from_std(options.timeout)
+            .map_err(|_| ContainerError::Validation("Drain timeout is too large".into()))?;
+        let record = DrainRecord {
+            id: uuid::Uuid::new_v4().to_string(),
+            cluster: cluster.to_string(),
+            node: node.to_string(),
+            options,
+            phase: DrainPhase::Draining,
+            pods: Vec::new(),
+            error: None,
+            started_at: now,
+            deadline: now + timeout,
+            updated_at: now,
+        };
+        self.store.save_drain(&record).await?;
+        info!(cluster, node, drain = %record.id, "Draining node");
+        self.run(record).await
+    }
+
+    pub async fn get_drain(&self, id: &str) -> ContainerResult<DrainRecord> {
+        self.store
+            .get_drain(id)
+            .await?
+            .ok_or_else(|| ContainerError::NotFound(format!("Drain {} not found", id)))
+    }
+
+    // Picks up every drain that was interrupted, e.g. by a controller restart
+    pub async fn resume_drains(&self) -> Vec<ContainerResult<DrainRecord>> {
+        let active = match self.store.list_active_drains().await {
+            Ok(active) => active,
+            Err(e) => return vec![Err(e)],
+        };
+        let mut results = Vec::new();
+        for record in active {
+            info!(cluster = %record.cluster, node = %record.node, drain = %record.id, "Resuming drain");
+            results.push(self.run(record).await);
+        }
+        results
+    }
+
+    // Stops a drain and makes the node schedulable again. Pods already evicted stay evicted.
+    pub async fn abort_drain(&self, id: &str) -> ContainerResult<DrainRecord> {
+        let _saving = self.saving.lock().await;
+        let mut record = self.get_drain(id).await?;
+        if record.phase.is_terminal() {
+            return Err(ContainerError::Validation(format!("Drain {} already finished as {:?}", id, record.phase)));
+        }
+        let kubeconfig = self.kubeconfigs.kubeconfig(&record.cluster).await?;
+        self.api.set_unschedulable(&kubeconfig, &record.node, false).await?;
+        record.finish(DrainPhase::Aborted, Some("Aborted; node uncordoned".into()));
+        self.store.save_drain(&record).await?;
+        info!(node = %record.node, drain = id, "Drain aborted");
+        Ok(record)
+    }
+
+    // Saves unless the drain was aborted meanwhile, in which case the aborted record is returned
+    async fn save(&self, record: &DrainRecord) -> ContainerResult<Option<DrainRecord>> {
+        let _saving = self.saving.lock().await;
+        if let Some(stored) = self.store.get_drain(&record.id).await?.filter(|r| r.phase == DrainPhase::Aborted) {
+            return Ok(Some(stored));
+        }
+        self.store.save_drain(record).await?;
+        Ok(None)
+    }
+
+    async fn run(&self, mut record: DrainRecord) -> ContainerResult<DrainRecord> {
+        let kubeconfig = self.kubeconfigs.kubeconfig(&record.cluster).await?;
+        // Idempotent, so a resumed drain simply re-asserts it
+        self.api.set_unschedulable(&kubeconfig, &record.node, true).await?;
+        let grace = record.options.grace_period_seconds;
+        loop {
+            let pods = self.api.node_pods(&kubeconfig, &record.node).await?;
+            let mut refused = Vec::new();
+            for pod in &pods {
+                let options = record.options.clone();
+                let entry = record.pod_mut(&pod.namespace, &pod.name);
+                if entry.status == EvictionStatus::Pending {
+                    if let Some(status) = classify(pod, &options) {
+                        entry.status = status;
+                    }
+                }
+                if let EvictionStatus::Refused(reason) = &entry.status {
+                    refused.push(format!("{}/{} ({})", pod.namespace, pod.name, reason));
+                }
+            }
+            // Like kubectl, refuse before evicting anything
+            if !refused.is_empty() {
+                record.finish(DrainPhase::Failed, Some(format!("Cannot drain {}: {}", record.node, refused.join(", "))));
+                return self.finish(record).await;
+            }
+
+            for pod in &pods {
+                let entry = record.pod_mut(&pod.namespace, &pod.name);
+                if entry.status != EvictionStatus::Pending {
+                    continue;
+                }
+                entry.attempts += 1;
+                let result = if pod.finished {
+                    self.api.delete_pod(&kubeconfig, &pod.namespace, &pod.name, grace).await.map(|_| EvictionResult::Accepted)
+                } else {
+                    self.api.evict_pod(&kubeconfig, &pod.namespace, &pod.name, grace).await
+                };
+                match result {
+                    Ok(EvictionResult::Accepted | EvictionResult::Gone) => {
+                        entry.status = EvictionStatus::Evicted;
+                        entry.last_error = None;
+                    }
+                    Ok(EvictionResult::Blocked(reason)) => entry.last_error = Some(reason),
+                    Err(e) => entry.last_error = Some(e.to_string()),
+                }
+            }
+
+            // Pods deleted by someone else between listings count as evicted
+            for entry in record.pods.iter_mut().filter(|p| p.status == EvictionStatus::Pending) {
+                if !pods.iter().any(|pod| pod.namespace == entry.namespace && pod.name == entry.name) {
+                    entry.status = EvictionStatus::Evicted;
+                }
+            }
+
+            // Done once nothing but skipped pods remain on the node
+            let remaining: Vec<&NodePod> = pods
+                .iter()
+                .filter(|pod| {
+                    let entry = record.pods.iter().find(|p| p.namespace == pod.namespace && p.name == pod.name);
+                    !matches!(entry.map(|e| &e.status), Some(EvictionStatus::Skipped(_)))
+                })
+                .collect();
+            let blocked: Vec<String> = record
+                .pods
+                .iter()
+                .filter(|p| p.status == EvictionStatus::Pending)
+                .map(|p| format!("{}/{}", p.namespace, p.name))
+                .collect();
+            if remaining.is_empty() {
+                record.finish(DrainPhase::Completed, None);
+                return self.finish(record).await;
+            }
+
+            if Utc::now() >= record.deadline {
+                if !record.options.force || blocked.is_empty() {
+                    let reason = if blocked.is_empty() {
+                        format!("{} pods still terminating", remaining.len())
+                    } else {
+                        format!("evictions still blocked: {}", blocked.join(", "))
+                    };
+                    record.finish(DrainPhase::Failed, Some(format!("Drain of {} timed out; {}", record.node, reason)));
+                    return self.finish(record).await;
+                }
+                for entry in record.pods.iter_mut().filter(|p| p.status == EvictionStatus::Pending) {
+                    warn!(node = %record.node, pod = %entry.name, namespace = %entry.namespace, "Force deleting pod blocked past the drain deadline");
+                    self.api.delete_pod(&kubeconfig, &entry.namespace, &entry.name, grace).await?;
+                    entry.status = EvictionStatus::Forced;
+                }
+                record.finish(DrainPhase::Completed, None);
+                return self.finish(record).await;
+            }
+
+            record.updated_at = Utc::now();
+            if let Some(aborted) = self.save(&record).await? {
+                return Ok(aborted);
+            }
+            tokio::time::sleep(self.retry_interval).await;
+        }
+    }
+
+    async fn finish(&self, record: DrainRecord) -> ContainerResult<DrainRecord> {
+        if let Some(aborted) = self.save(&record).await? {
+            return Ok(aborted);
+        }
+        match record.phase {
+            DrainPhase::Completed => info!(node = %record.node, drain = %record.id, "Node drained"),
+            _ => warn!(node = %record.node, drain = %record.id, "Drain failed: {}", record.error.as_deref().unwrap_or_default()),
+        }
+        Ok(record)
+    }
+
+    // Creates or updates the workload's PodDisruptionBudget, named after it
+    pub async fn ensure_pdb(&self, cluster: &str, workload: &WorkloadRef, min_available: MinAvailable) -> ContainerResult<PdbSpec> {
+        let kubeconfig = self.kubeconfigs.kubeconfig(cluster).await?;
+        let info = self.api.workload(&kubeconfig, workload).await?.ok_or_else(|| {
+            ContainerError::NotFound(format!("{:?} {}/{} not found", workload.kind, workload.namespace, workload.name))
+        })?;
+        self.apply_pdb(&kubeconfig, &info, min_available).await
+    }
+
+    // Gives every SirsiNexus-managed workload in the namespace a budget
+    pub async fn ensure_fleet_pdbs(&self, cluster: &str, namespace: &str, min_available: MinAvailable) -> ContainerResult<Vec<PdbSpec>> {
+        let kubeconfig = self.kubeconfigs.kubeconfig(cluster).await?;
+        let selector = format!("{}={}", MANAGED_BY_LABEL, MANAGED_BY_VALUE);
+        let mut applied = Vec::new();
+        for info in self.api.list_workloads(&kubeconfig, namespace, &selector).await? {
+            match self.apply_pdb(&kubeconfig, &info, min_available).await {
+                Ok(pdb) => applied.push(pdb),
+                // A single-replica workload cannot have a budget that still allows drains
+                Err(ContainerError::Validation(msg)) => warn!(namespace, workload = %info.workload.name, "Skipping PDB: {}", msg),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(applied)
+    }
+
+    async fn apply_pdb(&self, kubeconfig: &str, info: &WorkloadInfo, min_available: MinAvailable) -> ContainerResult<PdbSpec> {
+        let workload = &info.workload;
+        if info.selector.is_empty() {
+            return Err(ContainerError::Validation(format!("{} has no label selector to budget", workload.name)));
+        }
+        // A budget that leaves no room for a single eviction would block every drain
+        let blocks_all = match min_available {
+            MinAvailable::Pods(pods) => pods < 0 || pods >= info.replicas,
+            MinAvailable::Percent(percent) => percent >= 100 || (info.replicas * percent as i32 + 99) / 100 >= info.replicas,
+        };
+        if blocks_all {
+            return Err(ContainerError::Validation(format!(
+                "min available {:?} would block every eviction of {} with {} replicas",
+                min_available, workload.name, info.replicas
+            )));
+        }
+        let pdb = PdbSpec {
+            namespace: workload.namespace.clone(),
+            name: workload.name.clone(),
+            selector: info.selector.clone(),
+            min_available,
+        };
+        self.api.apply_pdb(kubeconfig, &pdb).await?;
+        Ok(pdb)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::sync::Mutex as StdMutex;
+
+    use super::*;
+
+    // A node's pods and a script of eviction answers per pod; the last answer repeats
+    #[derive(Default)]
+    struct FakeApi {
+        unschedulable: StdMutex<Option<bool>>,
+        pods: StdMutex<Vec<NodePod>>,
+        evictions: StdMutex<HashMap<String, VecDeque<EvictionResult>>>,
+        deleted: StdMutex<Vec<String>>,
+        workloads: StdMutex<Vec<WorkloadInfo>>,
+        pdbs: StdMutex<Vec<PdbSpec>>,
+    }
+
+    impl FakeApi {
+        fn pod(&self, name: &str, controller: Option<&str>) {
+            self.pods.lock().unwrap().push(NodePod {
+                namespace: "shop".into(),
+                name: name.into(),
+                controller_kind: controller.map(str::to_string),
+                mirror: false,
+                finished: false,
+                emptydir: false,
+            });
+        }
+
+        fn script(&self, name: &str, answers: Vec<EvictionResult>) {
+            self.evictions.lock().unwrap().insert(name.to_string(), answers.into());
+        }
+
+        fn remove(&self, name: &str) {
+            self.pods.lock().unwrap().retain(|p| p.name != name);
+        }
+    }
+
+    #[async_trait]
+    impl DrainApi for FakeApi {
+        async fn set_unschedulable(&self, _kubeconfig: &str, _node: &str, unschedulable: bool) -> ContainerResult<()> {
+            *self.unschedulable.lock().unwrap() = Some(unschedulable);
+            Ok(())
+        }
+
+        async fn node_pods(&self, _kubeconfig: &str, _node: &str) -> ContainerResult<Vec<NodePod>> {
+            Ok(self.pods.lock().unwrap().clone())
+        }
+
+        async fn evict_pod(&self, _kubeconfig: &str, _namespace: &str, name: &str, _grace: Option<i64>) -> ContainerResult<EvictionResult> {
+            let result = {
+                let mut evictions = self.evictions.lock().unwrap();
+                let answers = evictions.entry(name.to_string()).or_default();
+                if answers.len() > 1 { answers.pop_front() } else { answers.front().cloned() }.unwrap_or(EvictionResult::Accepted)
+            };
+            if result == EvictionResult::Accepted {
+                self.remove(name);
+            }
+            Ok(result)
+        }
+
+        async fn delete_pod(&self, _kubeconfig: &str, _namespace: &str, name: &str, _grace: Option<i64>) -> ContainerResult<()> {
+            self.deleted.lock().unwrap().push(name.to_string());
+            self.remove(name);
+            Ok(())
+        }
+
+        async fn workload(&self, _kubeconfig: &str, workload: &WorkloadRef) -> ContainerResult<Option<WorkloadInfo>> {
+            Ok(self.workloads.lock().unwrap().iter().find(|w| w.workload == *workload).cloned())
+        }
+
+        async fn list_workloads(&self, _kubeconfig: &str, _namespace: &str, _labels: &str) -> ContainerResult<Vec<WorkloadInfo>> {
+            Ok(self.workloads.lock().unwrap().clone())
+        }
+
+        async fn apply_pdb(&self, _kubeconfig: &str, pdb: &PdbSpec) -> ContainerResult<()> {
+            self.pdbs.lock().unwrap().push(pdb.clone());
+            Ok(())
+        }
+    }
+
+    fn drainer(api: Arc<FakeApi>, store: Arc<InMemoryDrainStore>) -> NodeDrainer {
+        let kubeconfigs = HashMap::from([("prod".to_string(), "apiVersion: v1".to_string())]);
+        NodeDrainer::new(api, Arc::new(kubeconfigs)).with_store(store).with_retry_interval(Duration::from_millis(2))
+    }
+
+    fn options(force: bool) -> DrainOptions {
+        DrainOptions { timeout: Duration::from_millis(40), force, ..Default::default() }
+    }
+
+    #[tokio::test]
+    async fn test_pdb_blocked_evictions_retry_until_deadline() {
+        let api = Arc::new(FakeApi::default());
+        api.pod("web-1", Some("ReplicaSet"));
+        api.pod("db-0", Some("StatefulSet"));
+        api.pod("node-exporter-x", Some("DaemonSet"));
+        let blocked = EvictionResult::Blocked("Cannot evict pod as it would violate the pod's disruption budget".into());
+        // web-1 frees up after two refusals; db-0 never does
+        api.script("web-1", vec![blocked.clone(), blocked.clone(), EvictionResult::Accepted]);
+        api.script("db-0", vec![blocked]);
+        let drainer = drainer(api.clone(), Arc::new(InMemoryDrainStore::new()));
+
+        let record = drainer.drain_node("prod", "node-a", options(false)).await.unwrap();
+        assert_eq!(record.phase, DrainPhase::Failed);
+        assert!(record.error.as_deref().unwrap().contains("evictions still blocked: shop/db-0"));
+        let pod = |name: &str| record.pods.iter().find(|p| p.name == name).unwrap().clone();
+        assert_eq!(pod("web-1").status, EvictionStatus::Evicted);
+        assert_eq!(pod("web-1").attempts, 3);
+        assert_eq!(pod("db-0").status, EvictionStatus::Pending);
+        assert!(pod("db-0").attempts > 3);
+        assert!(pod("db-0").last_error.unwrap().contains("disruption budget"));
+        assert!(matches!(pod("node-exporter-x").status, EvictionStatus::Skipped(_)));
+        // A timed-out drain leaves the node cordoned; aborting is what uncordons it
+        assert_eq!(*api.unschedulable.lock().unwrap(), Some(true));
+        assert!(api.deleted.lock().unwrap().is_empty());
+
+        // Forcing deletes what is still blocked at the deadline, and the unmanaged pod
+        api.pod("debug", None);
+        let record = drainer.drain_node("prod", "node-a", options(true)).await.unwrap();
+        assert_eq!(record.phase, DrainPhase::Completed);
+        let status = |name: &str| record.pods.iter().find(|p| p.name == name).unwrap().status.clone();
+        assert_eq!(status("db-0"), EvictionStatus::Forced);
+        assert_eq!(status("debug"), EvictionStatus::Evicted);
+        assert_eq!(*api.deleted.lock().unwrap(), vec!["db-0".to_string()]);
+        assert_eq!(api.pods.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_drain_resumes_after_restart_and_abort_uncordons() {
+        let api = Arc::new(FakeApi::default());
+        api.pod("web-1", Some("ReplicaSet"));
+        api.pod("web-2", Some("ReplicaSet"));
+        let store = Arc::new(InMemoryDrainStore::new());
+        let now = Utc::now();
+        // What a controller that died mid-drain left behind
+        let interrupted = DrainRecord {
+            id: "drain-1".into(),
+            cluster: "prod".into(),
+            node: "node-a".into(),
+            options: options(false),
+            phase: DrainPhase::Draining,
+            pods: vec![PodEviction {
+                namespace: "shop".into(),
+                name: "web-0".into(),
+                status: EvictionStatus::Evicted,
+                attempts: 1,
+                last_error: None,
+            }],
+            error: None,
+            started_at: now,
+            deadline: now + chrono::Duration::seconds(30),
+            updated_at: now,
+        };
+        store.save_drain(&interrupted).await.unwrap();
+
+        let restarted = drainer(api.clone(), store.clone());
+        assert!(restarted.drain_node("prod", "node-a", options(false)).await.is_err());
+        let resumed = restarted.resume_drains().await.into_iter().next().unwrap().unwrap();
+        assert_eq!(resumed.phase, DrainPhase::Completed);
+        let names: Vec<&str> = resumed.pods.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["web-0", "web-1", "web-2"]);
+        assert!(store.list_active_drains().await.unwrap().is_empty());
+
+        // A drain stuck on a budget can be abandoned, making the node schedulable again
+        api.pod("db-0", Some("StatefulSet"));
+        api.script("db-0", vec![EvictionResult::Blocked("budget".into())]);
+        let drainer = Arc::new(drainer(api.clone(), store.clone()));
+        let mut slow = options(false);
+        slow.timeout = Duration::from_secs(30);
+        let running = tokio::spawn({
+            let drainer = drainer.clone();
+            async move { drainer.drain_node("prod", "node-a", slow).await }
+        });
+        let id = loop {
+            if let Some(active) = store.list_active_drains().await.unwrap().pop() {
+                break active.id;
+            }
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        };
+        let aborted = drainer.abort_drain(&id).await.unwrap();
+        assert_eq!(aborted.phase, DrainPhase::Aborted);
+        assert_eq!(running.await.unwrap().unwrap().phase, DrainPhase::Aborted);
+        assert_eq!(*api.unschedulable.lock().unwrap(), Some(false));
+    }
+
+    #[tokio::test]
+    async fn test_ensure_pdb_rejects_budgets_that_block_drains() {
+        let api = Arc::new(FakeApi::default());
+        let workload = |name: &str, replicas: i32| WorkloadInfo {
+            workload: WorkloadRef { namespace: "shop".into(), kind: WorkloadKind::Deployment, name: name.into() },
+            replicas,
+            selector: BTreeMap::from([("app".to_string(), name.to_string())]),
+        };
+        api.workloads.lock().unwrap().extend([workload("web", 3), workload("cron", 1)]);
+        let drainer = drainer(api.clone(), Arc::new(InMemoryDrainStore::new()));
+
+        let web = workload("web", 3).workload;
+        assert!(drainer.ensure_pdb("prod", &web, MinAvailable::Pods(3)).await.is_err());
+        assert!(drainer.ensure_pdb("prod", &web, MinAvailable::Percent(90)).await.is_err());
+        let pdb = drainer.ensure_pdb("prod", &web, MinAvailable::Percent(50)).await.unwrap();
+        assert_eq!(pdb.selector.get("app").map(String::as_str), Some("web"));
+
+        let fleet = drainer.ensure_fleet_pdbs("prod", "shop", MinAvailable::Pods(1)).await.unwrap();
+        assert_eq!(fleet.iter().map(|p| p.name.as_str()).collect::<Vec<_>>(), vec!["web"]);
+        assert_eq!(api.pdbs.lock().unwrap().len(), 2);
+    }
+}
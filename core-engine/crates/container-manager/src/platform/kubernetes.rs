@@ -1,17 +1,52 @@
+use async_trait::async_trait;
 use k8s_openapi::api::{
-    apps::v1::{Deployment, DeploymentSpec, DeploymentStatus},
-    core::v1::{Container, EnvVar, PodSpec, PodTemplateSpec, Service, ServiceSpec},
+    apps::v1::{DaemonSet, Deployment, DeploymentSpec, DeploymentStatus, StatefulSet},
+    batch::v1::Job,
+    core::v1::{
+        Container, EnvVar, Event, EventSource, Namespace, Node, ObjectReference, PersistentVolumeClaim, Pod, PodSpec, PodTemplateSpec,
+        Secret, Service, ServiceSpec, TypedLocalObjectReference,
+    },
+    policy::v1::{PodDisruptionBudget, PodDisruptionBudgetSpec},
+    rbac::v1::{ClusterRoleBinding, RoleRef, Subject},
 };
+use k8s_openapi::apiextensions_apiserver::pkg::apis::apiextensions::v1::CustomResourceDefinition;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{LabelSelector, ObjectMeta, Time};
+use k8s_openapi::apimachinery::pkg::util::intstr::IntOrString;
+use k8s_openapi::ByteString;
 use kube::{
-    api::{Api, ListParams, PostParams},
+    api::{
+        Api, ApiResource, DeleteParams, DynamicObject, EvictParams, GroupVersionKind, ListParams, Patch, PatchParams, PostParams, TypeMeta,
+    },
     client::Client,
-    config::{KubeConfig, Kubeconfig},
+    config::{KubeConfig, KubeConfigOptions, Kubeconfig},
+    core::ErrorResponse,
+    discovery::{self, verbs, Discovery, Scope},
     Config, ResourceExt,
 };
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+use tracing::warn;
 
+use super::cluster::{ClusterAccess, KubeconfigSource, RbacBinding, SubjectKind};
+use super::drain::{DrainApi, EvictionResult, MinAvailable, NodePod, PdbSpec, WorkloadInfo, WorkloadKind, WorkloadRef};
+use super::migration::{MigrationApi, TransferMethod, VolumeTransfer, WorkloadReadiness};
+use super::multicluster::{ClusterApi, ClusterRegistry, NodeSummary};
+use super::secret_sync::{KubeSecret, SecretSyncApi};
 use crate::error::{ContainerError, ContainerResult};
+use crate::service::helm::ReleaseHealth;
+use crate::service::{MANAGED_BY_LABEL, MANAGED_BY_VALUE};
+
+// Namespaces owned by Kubernetes or the cloud provider, ignored by the empty-cluster check
+const SYSTEM_NAMESPACES: &[&str] = &[
+    "kube-system",
+    "kube-public",
+    "kube-node-lease",
+    "gmp-system",
+    "gke-managed-system",
+    "amazon-cloudwatch",
+];
 
 pub struct KubernetesClient {
     client: Client,
@@ -57,6 +92,12 @@ impl KubernetesClient {
         })
     }
 
+    // A client for a registered cluster, using its current credentials
+    pub async fn for_cluster(registry: &ClusterRegistry, cluster: &str, namespace: impl Into<String>) -> ContainerResult<Self> {
+        let kubeconfig = registry.kubeconfig(cluster).await?;
+        Self::new(KubernetesConfig { kubeconfig: Some(kubeconfig), context: None, namespace: namespace.into(), in_cluster: false }).await
+    }
+
     pub async fn list_deployments(&self, labels: Option<&str>) -> ContainerResult<Vec<Deployment>> {
         let api: Api<Deployment> = Api::namespaced(self.client.clone(), &self.namespace);
         let params = if let Some(label_selector) = labels {
@@ -189,10 +230,878 @@ impl KubernetesClient {
     }
 }
 
+// `ClusterAccess` over the kubeconfig a `ClusterProvisioner` handed back
+#[derive(Debug, Default, Clone)]
+pub struct KubeClusterAccess;
+
+impl KubeClusterAccess {
+    pub fn new() -> Self {
+        Self
+    }
+
+    async fn client(kubeconfig: &str) -> ContainerResult<Client> {
+        let kubeconfig = Kubeconfig::from_yaml(kubeconfig).map_err(|e| ContainerError::Config(format!("Invalid kubeconfig: {}", e)))?;
+        let config = Config::from_custom_kubeconfig(kubeconfig, &KubeConfigOptions::default())
+            .await
+            .map_err(|e| ContainerError::Config(format!("Failed to create config: {}", e)))?;
+        Client::try_from(config).map_err(|e| ContainerError::Platform(format!("Failed to create client: {}", e)))
+    }
+
+    async fn names<K>(api: Api<K>, kind: &str) -> ContainerResult<Vec<String>>
+    where
+        K: kube::Resource + Clone + serde::de::DeserializeOwned + std::fmt::Debug,
+    {
+        let items = api
+            .list(&ListParams::default())
+            .await
+            .map_err(|e| ContainerError::Platform(format!("Failed to list {}s: {}", kind, e)))?;
+        Ok(items
+            .items
+            .iter()
+            .filter_map(|item| {
+                let namespace = item.namespace().unwrap_or_default();
+                (!SYSTEM_NAMESPACES.contains(&namespace.as_str())).then(|| format!("{}/{}/{}", namespace, kind, item.name_any()))
+            })
+            .collect())
+    }
+}
+
+#[async_trait]
+impl ClusterAccess for KubeClusterAccess {
+    async fn apply_rbac(&self, kubeconfig: &str, bindings: &[RbacBinding]) -> ContainerResult<()> {
+        let api: Api<ClusterRoleBinding> = Api::all(Self::client(kubeconfig).await?);
+        for binding in bindings {
+            let kind = match binding.kind {
+                SubjectKind::User => "User",
+                SubjectKind::Group => "Group",
+            };
+            let resource = ClusterRoleBinding {
+                metadata: ObjectMeta { name: Some(binding.name.clone()), ..Default::default() },
+                role_ref: RoleRef {
+                    api_group: "rbac.authorization.k8s.io".to_string(),
+                    kind: "ClusterRole".to_string(),
+                    name: binding.cluster_role.clone(),
+                },
+                subjects: Some(
+                    binding
+                        .subjects
+                        .iter()
+                        .map(|name| Subject {
+                            api_group: Some("rbac.authorization.k8s.io".to_string()),
+                            kind: kind.to_string(),
+                            name: name.clone(),
+                            namespace: None,
+                        })
+                        .collect(),
+                ),
+            };
+            api.patch(&binding.name, &PatchParams::apply("sirsi-container-manager").force(), &Patch::Apply(&resource))
+                .await
+                .map_err(|e| ContainerError::Platform(format!("Failed to apply cluster role binding {}: {}", binding.name, e)))?;
+        }
+        Ok(())
+    }
+
+    async fn user_workloads(&self, kubeconfig: &str) -> ContainerResult<Vec<String>> {
+        let client = Self::client(kubeconfig).await?;
+        let mut workloads = Self::names(Api::<Deployment>::all(client.clone()), "Deployment").await?;
+        workloads.extend(Self::names(Api::<StatefulSet>::all(client.clone()), "StatefulSet").await?);
+        workloads.extend(Self::names(Api::<DaemonSet>::all(client), "DaemonSet").await?);
+        Ok(workloads)
+    }
+}
+
+// A workload is healthy once its controller has caught up with the latest spec and every
+// replica is ready
+#[async_trait]
+impl ReleaseHealth for KubeClusterAccess {
+    async fn unhealthy_workloads(&self, kubeconfig: &str, namespace: &str, release: &str) -> ContainerResult<Vec<String>> {
+        let client = Self::client(kubeconfig).await?;
+        let params = ListParams::default().labels(&format!("app.kubernetes.io/instance={}", release));
+        let list_error = |kind: &str, e: kube::Error| ContainerError::Platform(format!("Failed to list {}s: {}", kind, e));
+        let mut unhealthy = Vec::new();
+
+        let deployments = Api::<Deployment>::namespaced(client.clone(), namespace)
+            .list(&params)
+            .await
+            .map_err(|e| list_error("deployment", e))?;
+        for deployment in deployments.items {
+            let wanted = deployment.spec.as_ref().and_then(|s| s.replicas).unwrap_or(1);
+            let status = deployment.status.clone().unwrap_or_default();
+            let current = status.observed_generation >= deployment.metadata.generation;
+            if !current || status.updated_replicas.unwrap_or(0) < wanted || status.available_replicas.unwrap_or(0) < wanted {
+                unhealthy.push(format!("Deployment/{}", deployment.name_any()));
+            }
+        }
+
+        let statefulsets = Api::<StatefulSet>::namespaced(client.clone(), namespace)
+            .list(&params)
+            .await
+            .map_err(|e| list_error("statefulset", e))?;
+        for statefulset in statefulsets.items {
+            let wanted = statefulset.spec.as_ref().and_then(|s| s.replicas).unwrap_or(1);
+            let status = statefulset.status.clone().unwrap_or_default();
+            if status.ready_replicas.unwrap_or(0) < wanted || status.updated_replicas.unwrap_or(0) < wanted {
+                unhealthy.push(format!("StatefulSet/{}", statefulset.name_any()));
+            }
+        }
+
+        let daemonsets = Api::<DaemonSet>::namespaced(client, namespace)
+            .list(&params)
+            .await
+            .map_err(|e| list_error("daemonset", e))?;
+        for daemonset in daemonsets.items {
+            let status = daemonset.status.clone().unwrap_or_default();
+            if status.number_ready < status.desired_number_scheduled
+                || status.updated_number_scheduled.unwrap_or(0) < status.desired_number_scheduled
+            {
+                unhealthy.push(format!("DaemonSet/{}", daemonset.name_any()));
+            }
+        }
+        Ok(unhealthy)
+    }
+}
+
+#[async_trait]
+impl DrainApi for KubeClusterAccess {
+    async fn set_unschedulable(&self, kubeconfig: &str, node: &str, unschedulable: bool) -> ContainerResult<()> {
+        let api: Api<Node> = Api::all(Self::client(kubeconfig).await?);
+        let patch = serde_json::json!({ "spec": { "unschedulable": unschedulable } });
+        api.patch(node, &PatchParams::default(), &Patch::Merge(&patch))
+            .await
+            .map_err(|e| ContainerError::Platform(format!("Failed to update node {}: {}", node, e)))?;
+        Ok(())
+    }
+
+    async fn node_pods(&self, kubeconfig: &str, node: &str) -> ContainerResult<Vec<NodePod>> {
+        let api: Api<Pod> = Api::all(Self::client(kubeconfig).await?);
+        let pods = api
+            .list(&ListParams::default().fields(&format!("spec.nodeName={}", node)))
+            .await
+            .map_err(|e| ContainerError::Platform(format!("Failed to list pods on {}: {}", node, e)))?;
+        Ok(pods
+            .items
+            .into_iter()
+            .map(|pod| {
+                let controller_kind = pod
+                    .metadata
+                    .owner_references
+                    .iter()
+                    .flatten()
+                    .find(|owner| owner.controller == Some(true))
+                    .map(|owner| owner.kind.clone());
+                let phase = pod.status.as_ref().and_then(|s| s.phase.as_deref());
+                NodePod {
+                    namespace: pod.namespace().unwrap_or_default(),
+                    name: pod.name_any(),
+                    controller_kind,
+                    mirror: pod.annotations().contains_key("kubernetes.io/config.mirror"),
+                    finished: matches!(phase, Some("Succeeded") | Some("Failed")),
+                    emptydir: pod
+                        .spec
+                        .as_ref()
+                        .and_then(|s| s.volumes.as_ref())
+                        .is_some_and(|volumes| volumes.iter().any(|v| v.empty_dir.is_some())),
+                }
+            })
+            .collect())
+    }
+
+    async fn evict_pod(
+        &self,
+        kubeconfig: &str,
+        namespace: &str,
+        name: &str,
+        grace_period_seconds: Option<i64>,
+    ) -> ContainerResult<EvictionResult> {
+        let api: Api<Pod> = Api::namespaced(Self::client(kubeconfig).await?, namespace);
+        let params = EvictParams {
+            delete_options: Some(DeleteParams { grace_period_seconds: grace_period_seconds.map(|g| g as u32), ..Default::default() }),
+            ..Default::default()
+        };
+        match api.evict(name, &params).await {
+            Ok(_) => Ok(EvictionResult::Accepted),
+            // 429 is how the API server says a disruption budget would be violated
+            Err(kube::Error::Api(response)) if response.code == 429 => Ok(EvictionResult::Blocked(response.message)),
+            Err(kube::Error::Api(response)) if response.code == 404 => Ok(EvictionResult::Gone),
+            Err(e) => Err(ContainerError::Platform(format!("Failed to evict pod {}/{}: {}", namespace, name, e))),
+        }
+    }
+
+    async fn delete_pod(&self, kubeconfig: &str, namespace: &str, name: &str, grace_period_seconds: Option<i64>) -> ContainerResult<()> {
+        let api: Api<Pod> = Api::namespaced(Self::client(kubeconfig).await?, namespace);
+        let params = DeleteParams { grace_period_seconds: grace_period_seconds.map(|g| g as u32), ..Default::default() };
+        match api.delete(name, &params).await {
+            Ok(_) => Ok(()),
+            Err(kube::Error::Api(response)) if response.code == 404 => Ok(()),
+            Err(e) => Err(ContainerError::Platform(format!("Failed to delete pod {}/{}: {}", namespace, name, e))),
+        }
+    }
+
+    async fn workload(&self, kubeconfig: &str, workload: &WorkloadRef) -> ContainerResult<Option<WorkloadInfo>> {
+        let client = Self::client(kubeconfig).await?;
+        let get_error = |e: kube::Error| ContainerError::Platform(format!("Failed to get {}: {}", workload.name, e));
+        let found = match workload.kind {
+            WorkloadKind::Deployment => Api::<Deployment>::namespaced(client, &workload.namespace)
+                .get_opt(&workload.name)
+                .await
+                .map_err(get_error)?
+                .map(|d| deployment_info(&workload.namespace, d)),
+            WorkloadKind::StatefulSet => Api::<StatefulSet>::namespaced(client, &workload.namespace)
+                .get_opt(&workload.name)
+                .await
+                .map_err(get_error)?
+                .map(|s| statefulset_info(&workload.namespace, s)),
+        };
+        Ok(found)
+    }
+
+    async fn list_workloads(&self, kubeconfig: &str, namespace: &str, labels: &str) -> ContainerResult<Vec<WorkloadInfo>> {
+        let client = Self::client(kubeconfig).await?;
+        let params = ListParams::default().labels(labels);
+        let list_error = |kind: &str, e: kube::Error| ContainerError::Platform(format!("Failed to list {}s: {}", kind, e));
+        let deployments = Api::<Deployment>::namespaced(client.clone(), namespace)
+            .list(&params)
+            .await
+            .map_err(|e| list_error("deployment", e))?;
+        let statefulsets = Api::<StatefulSet>::namespaced(client, namespace)
+            .list(&params)
+            .await
+            .map_err(|e| list_error("statefulset", e))?;
+        Ok(deployments
+            .items
+            .into_iter()
+            .map(|d| deployment_info(namespace, d))
+            .chain(statefulsets.items.into_iter().map(|s| statefulset_info(namespace, s)))
+            .collect())
+    }
+
+    async fn apply_pdb(&self, kubeconfig: &str, pdb: &PdbSpec) -> ContainerResult<()> {
+        let api: Api<PodDisruptionBudget> = Api::namespaced(Self::client(kubeconfig).await?, &pdb.namespace);
+        let min_available = match pdb.min_available {
+            MinAvailable::Pods(pods) => IntOrString::Int(pods),
+            MinAvailable::Percent(percent) => IntOrString::String(format!("{}%", percent)),
+        };
+        let resource = PodDisruptionBudget {
+            metadata: ObjectMeta {
+                name: Some(pdb.name.clone()),
+                namespace: Some(pdb.namespace.clone()),
+                labels: Some(BTreeMap::from([(MANAGED_BY_LABEL.to_string(), MANAGED_BY_VALUE.to_string())])),
+                ..Default::default()
+            },
+            spec: Some(PodDisruptionBudgetSpec {
+                min_available: Some(min_available),
+                selector: Some(LabelSelector { match_labels: Some(pdb.selector.clone()), ..Default::default() }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        api.patch(&pdb.name, &PatchParams::apply("sirsi-container-manager").force(), &Patch::Apply(&resource))
+            .await
+            .map_err(|e| ContainerError::Platform(format!("Failed to apply disruption budget {}: {}", pdb.name, e)))?;
+        Ok(())
+    }
+}
+
+// 401s become `Permission` so the registry knows to refresh the cluster's token
+fn cluster_api_error(what: &str, e: kube::Error) -> ContainerError {
+    match e {
+        kube::Error::Api(response) if response.code == 401 => ContainerError::Permission(format!("Failed to {}: {}", what, response.message)),
+        e => ContainerError::Platform(format!("Failed to {}: {}", what, e)),
+    }
+}
+
+#[async_trait]
+impl ClusterApi for KubeClusterAccess {
+    async fn server_version(&self, kubeconfig: &str) -> ContainerResult<String> {
+        let info = Self::client(kubeconfig)
+            .await?
+            .apiserver_version()
+            .await
+            .map_err(|e| cluster_api_error("read the server version", e))?;
+        Ok(info.git_version)
+    }
+
+    async fn nodes(&self, kubeconfig: &str) -> ContainerResult<Vec<NodeSummary>> {
+        let api: Api<Node> = Api::all(Self::client(kubeconfig).await?);
+        let nodes = api.list(&ListParams::default()).await.map_err(|e| cluster_api_error("list nodes", e))?;
+        Ok(nodes
+            .items
+            .into_iter()
+            .map(|node| {
+                let status = node.status.unwrap_or_default();
+                let ready = status.conditions.iter().flatten().any(|c| c.type_ == "Ready" && c.status == "True");
+                NodeSummary {
+                    name: node.metadata.name.unwrap_or_default(),
+                    ready,
+                    kubelet_version: status.node_info.map(|info| info.kubelet_version).unwrap_or_default(),
+                }
+            })
+            .collect())
+    }
+
+    async fn workloads(&self, kubeconfig: &str, selector: &str) -> ContainerResult<Vec<WorkloadInfo>> {
+        let client = Self::client(kubeconfig).await?;
+        let params = ListParams::default().labels(selector);
+        let deployments = Api::<Deployment>::all(client.clone())
+            .list(&params)
+            .await
+            .map_err(|e| cluster_api_error("list deployments", e))?;
+        let statefulsets = Api::<StatefulSet>::all(client)
+            .list(&params)
+            .await
+            .map_err(|e| cluster_api_error("list statefulsets", e))?;
+        Ok(deployments
+            .items
+            .into_iter()
+            .map(|d| deployment_info(&d.namespace().unwrap_or_default(), d))
+            .chain(statefulsets.items.into_iter().map(|s| statefulset_info(&s.namespace().unwrap_or_default(), s)))
+            .collect())
+    }
+}
+
+fn deployment_info(namespace: &str, deployment: Deployment) -> WorkloadInfo {
+    let spec = deployment.spec.unwrap_or_default();
+    WorkloadInfo {
+        workload: WorkloadRef {
+            namespace: namespace.to_string(),
+            kind: WorkloadKind::Deployment,
+            name: deployment.metadata.name.unwrap_or_default(),
+        },
+        replicas: spec.replicas.unwrap_or(1),
+        selector: spec.selector.match_labels.unwrap_or_default(),
+    }
+}
+
+fn statefulset_info(namespace: &str, statefulset: StatefulSet) -> WorkloadInfo {
+    let spec = statefulset.spec.unwrap_or_default();
+    WorkloadInfo {
+        workload: WorkloadRef {
+            namespace: namespace.to_string(),
+            kind: WorkloadKind::StatefulSet,
+            name: statefulset.metadata.name.unwrap_or_default(),
+        },
+        replicas: spec.replicas.unwrap_or(1),
+        selector: spec.selector.match_labels.unwrap_or_default(),
+    }
+}
+
+fn kube_secret(secret: Secret) -> KubeSecret {
+    KubeSecret {
+        namespace: secret.metadata.namespace.unwrap_or_default(),
+        name: secret.metadata.name.unwrap_or_default(),
+        type_: secret.type_.unwrap_or_else(|| "Opaque".to_string()),
+        data: secret.data.unwrap_or_default().into_iter().map(|(k, v)| (k, v.0)).collect(),
+        labels: secret.metadata.labels.unwrap_or_default(),
+        annotations: secret.metadata.annotations.unwrap_or_default(),
+    }
+}
+
+#[async_trait]
+impl SecretSyncApi for KubeClusterAccess {
+    async fn get_secret(&self, kubeconfig: &str, namespace: &str, name: &str) -> ContainerResult<Option<KubeSecret>> {
+        let api: Api<Secret> = Api::namespaced(Self::client(kubeconfig).await?, namespace);
+        let secret = api
+            .get_opt(name)
+            .await
+            .map_err(|e| ContainerError::Platform(format!("Failed to get secret {}/{}: {}", namespace, name, e)))?;
+        Ok(secret.map(kube_secret))
+    }
+
+    async fn put_secret(&self, kubeconfig: &str, secret: &KubeSecret) -> ContainerResult<()> {
+        let api: Api<Secret> = Api::namespaced(Self::client(kubeconfig).await?, &secret.namespace);
+        let put_error = |e: kube::Error| ContainerError::Platform(format!("Failed to write secret {}/{}: {}", secret.namespace, secret.name, e));
+        let mut resource = Secret {
+            metadata: ObjectMeta {
+                name: Some(secret.name.clone()),
+                namespace: Some(secret.namespace.clone()),
+                labels: Some(secret.labels.clone()),
+                annotations: Some(secret.annotations.clone()),
+                ..Default::default()
+            },
+            type_: Some(secret.type_.clone()),
+            data: Some(secret.data.iter().map(|(k, v)| (k.clone(), ByteString(v.clone()))).collect()),
+            ..Default::default()
+        };
+        match api.get_opt(&secret.name).await.map_err(put_error)? {
+            // A Secret's type is immutable, so a changed one means recreating it
+            Some(current) if current.type_.as_deref().unwrap_or("Opaque") != secret.type_ => {
+                api.delete(&secret.name, &DeleteParams::default()).await.map_err(put_error)?;
+                api.create(&PostParams::default(), &resource).await.map_err(put_error)?;
+            }
+            Some(current) => {
+                resource.metadata.resource_version = current.metadata.resource_version;
+                api.replace(&secret.name, &PostParams::default(), &resource).await.map_err(put_error)?;
+            }
+            None => {
+                api.create(&PostParams::default(), &resource).await.map_err(put_error)?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn delete_secret(&self, kubeconfig: &str, namespace: &str, name: &str) -> ContainerResult<()> {
+        let api: Api<Secret> = Api::namespaced(Self::client(kubeconfig).await?, namespace);
+        match api.delete(name, &DeleteParams::default()).await {
+            Ok(_) => Ok(()),
+            Err(kube::Error::Api(response)) if response.code == 404 => Ok(()),
+            Err(e) => Err(ContainerError::Platform(format!("Failed to delete secret {}/{}: {}", namespace, name, e))),
+        }
+    }
+
+    async fn list_secrets(&self, kubeconfig: &str, namespace: &str, labels: &str) -> ContainerResult<Vec<KubeSecret>> {
+        let api: Api<Secret> = Api::namespaced(Self::client(kubeconfig).await?, namespace);
+        let secrets = api
+            .list(&ListParams::default().labels(labels))
+            .await
+            .map_err(|e| ContainerError::Platform(format!("Failed to list secrets in {}: {}", namespace, e)))?;
+        Ok(secrets.items.into_iter().map(kube_secret).collect())
+    }
+
+    async fn record_event(&self, kubeconfig: &str, namespace: &str, secret: &str, reason: &str, message: &str) -> ContainerResult<()> {
+        let api: Api<Event> = Api::namespaced(Self::client(kubeconfig).await?, namespace);
+        let now = Time(chrono::Utc::now());
+        let event = Event {
+            metadata: ObjectMeta {
+                generate_name: Some(format!("{}.", secret)),
+                namespace: Some(namespace.to_string()),
+                ..Default::default()
+            },
+            involved_object: ObjectReference {
+                api_version: Some("v1".to_string()),
+                kind: Some("Secret".to_string()),
+                name: Some(secret.to_string()),
+                namespace: Some(namespace.to_string()),
+                ..Default::default()
+            },
+            reason: Some(reason.to_string()),
+            message: Some(message.to_string()),
+            type_: Some("Warning".to_string()),
+            source: Some(EventSource { component: Some("sirsi-container-manager".to_string()), host: None }),
+            first_timestamp: Some(now.clone()),
+            last_timestamp: Some(now),
+            count: Some(1),
+            ..Default::default()
+        };
+        api.create(&PostParams::default(), &event)
+            .await
+            .map_err(|e| ContainerError::Platform(format!("Failed to record event for {}/{}: {}", namespace, secret, e)))?;
+        Ok(())
+    }
+}
+
+fn split_api_version(api_version: &str) -> (&str, &str) {
+    api_version.split_once('/').unwrap_or(("", api_version))
+}
+
+fn migration_error(what: &str, e: impl std::fmt::Display) -> ContainerError {
+    ContainerError::Platform(format!("Failed to {}: {}", what, e))
+}
+
+#[async_trait]
+impl MigrationApi for KubeClusterAccess {
+    async fn export_namespace(&self, kubeconfig: &str, namespace: &str) -> ContainerResult<Vec<Value>> {
+        let client = Self::client(kubeconfig).await?;
+        let discovery = Discovery::new(client.clone()).run().await.map_err(|e| migration_error("discover API resources", e))?;
+        let mut objects = Vec::new();
+        for group in discovery.groups() {
+            for (resource, capabilities) in group.recommended_resources() {
+                if !matches!(capabilities.scope, Scope::Namespaced) || !capabilities.supports_operation(verbs::LIST) {
+                    continue;
+                }
+                let api: Api<DynamicObject> = Api::namespaced_with(client.clone(), namespace, &resource);
+                let list = api
+                    .list(&ListParams::default())
+                    .await
+                    .map_err(|e| migration_error(&format!("list {}", resource.plural), e))?;
+                for mut object in list.items {
+                    // List items come back without their type
+                    object.types = Some(TypeMeta { api_version: resource.api_version.clone(), kind: resource.kind.clone() });
+                    objects.push(serde_json::to_value(object).map_err(|e| migration_error("serialize an object", e))?);
+                }
+            }
+        }
+        Ok(objects)
+    }
+
+    async fn custom_resource_definition(&self, kubeconfig: &str, api_version: &str, kind: &str) -> ContainerResult<Option<Value>> {
+        // Custom resource groups are always domain names; built-in groups like `apps` are not
+        let (group, _) = split_api_version(api_version);
+        if !group.contains('.') {
+            return Ok(None);
+        }
+        let api: Api<CustomResourceDefinition> = Api::all(Self::client(kubeconfig).await?);
+        let definitions = api
+            .list(&ListParams::default())
+            .await
+            .map_err(|e| migration_error("list custom resource definitions", e))?;
+        definitions
+            .items
+            .into_iter()
+            .find(|d| d.spec.group == group && d.spec.names.kind == kind)
+            .map(|d| serde_json::to_value(d).map_err(|e| migration_error("serialize a custom resource definition", e)))
+            .transpose()
+    }
+
+    async fn ensure_namespace(&self, kubeconfig: &str, namespace: &str) -> ContainerResult<()> {
+        let api: Api<Namespace> = Api::all(Self::client(kubeconfig).await?);
+        let resource = Namespace { metadata: ObjectMeta { name: Some(namespace.to_string()), ..Default::default() }, ..Default::default() };
+        api.patch(namespace, &PatchParams::apply("sirsi-container-manager"), &Patch::Apply(&resource))
+            .await
+            .map_err(|e| migration_error(&format!("create namespace {}", namespace), e))?;
+        Ok(())
+    }
+
+    async fn apply(&self, kubeconfig: &str, object: &Value) -> ContainerResult<()> {
+        let client = Self::client(kubeconfig).await?;
+        let text = |pointer: &str| object.pointer(pointer).and_then(Value::as_str).unwrap_or_default().to_string();
+        let (kind, name) = (text("/kind"), text("/metadata/name"));
+        let (group, version) = split_api_version(object.pointer("/apiVersion").and_then(Value::as_str).unwrap_or_default());
+        let (resource, capabilities) = discovery::pinned_kind(&client, &GroupVersionKind::gvk(group, version, &kind))
+            .await
+            .map_err(|e| migration_error(&format!("resolve {}", kind), e))?;
+        let api: Api<DynamicObject> = match capabilities.scope {
+            Scope::Namespaced => Api::namespaced_with(client, &text("/metadata/namespace"), &resource),
+            Scope::Cluster => Api::all_with(client, &resource),
+        };
+        api.patch(&name, &PatchParams::apply("sirsi-container-manager").force(), &Patch::Apply(object))
+            .await
+            .map_err(|e| migration_error(&format!("apply {} {}", kind, name), e))?;
+        Ok(())
+    }
+
+    async fn scale(&self, kubeconfig: &str, workload: &WorkloadRef, replicas: i32) -> ContainerResult<()> {
+        let client = Self::client(kubeconfig).await?;
+        let patch = Patch::Merge(serde_json::json!({ "spec": { "replicas": replicas } }));
+        let result = match workload.kind {
+            WorkloadKind::Deployment => Api::<Deployment>::namespaced(client, &workload.namespace)
+                .patch(&workload.name, &PatchParams::default(), &patch)
+                .await
+                .map(|_| ()),
+            WorkloadKind::StatefulSet => Api::<StatefulSet>::namespaced(client, &workload.namespace)
+                .patch(&workload.name, &PatchParams::default(), &patch)
+                .await
+                .map(|_| ()),
+        };
+        result.map_err(|e| migration_error(&format!("scale {}", workload.name), e))
+    }
+
+    async fn readiness(&self, kubeconfig: &str, namespace: &str) -> ContainerResult<Vec<WorkloadReadiness>> {
+        let client = Self::client(kubeconfig).await?;
+        let deployments = Api::<Deployment>::namespaced(client.clone(), namespace)
+            .list(&ListParams::default())
+            .await
+            .map_err(|e| migration_error("list deployments", e))?;
+        let statefulsets = Api::<StatefulSet>::namespaced(client, namespace)
+            .list(&ListParams::default())
+            .await
+            .map_err(|e| migration_error("list statefulsets", e))?;
+        let readiness = |kind: WorkloadKind, name: Option<String>, desired: Option<i32>, replicas: i32, ready: Option<i32>| {
+            WorkloadReadiness {
+                workload: WorkloadRef { namespace: namespace.to_string(), kind, name: name.unwrap_or_default() },
+                desired: desired.unwrap_or(1),
+                replicas,
+                ready: ready.unwrap_or_default(),
+            }
+        };
+        Ok(deployments
+            .items
+            .into_iter()
+            .map(|d| {
+                let status = d.status.unwrap_or_default();
+                let desired = d.spec.and_then(|s| s.replicas);
+                readiness(WorkloadKind::Deployment, d.metadata.name, desired, status.replicas.unwrap_or_default(), status.ready_replicas)
+            })
+            .chain(statefulsets.items.into_iter().map(|s| {
+                let status = s.status.unwrap_or_default();
+                let desired = s.spec.and_then(|s| s.replicas);
+                readiness(WorkloadKind::StatefulSet, s.metadata.name, desired, status.replicas, status.ready_replicas)
+            }))
+            .collect())
+    }
+}
+
+// Copies claims between clusters. Rsync runs a daemon next to a new target claim, behind a
+// LoadBalancer, and a job on the source pushing to it; the daemon takes a per-transfer password
+// but the traffic is not encrypted, so the clusters should share a private network. Snapshots
+// are re-imported on the target by handle, which needs both clusters on the same storage backend.
+pub struct KubeVolumeTransfer {
+    image: String,
+    timeout: Duration,
+    poll_interval: Duration,
+}
+
+impl KubeVolumeTransfer {
+    pub fn new() -> Self {
+        Self {
+            image: "instrumentisto/rsync-ssh:alpine".to_string(),
+            timeout: Duration::from_secs(3600),
+            poll_interval: Duration::from_secs(5),
+        }
+    }
+
+    pub fn with_image(mut self, image: impl Into<String>) -> Self {
+        self.image = image.into();
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    async fn until<T, F, Fut>(&self, what: &str, mut check: F) -> ContainerResult<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: std::future::Future<Output = ContainerResult<Option<T>>>,
+    {
+        let deadline = tokio::time::Instant::now() + self.timeout;
+        loop {
+            if let Some(value) = check().await? {
+                return Ok(value);
+            }
+            if tokio::time::Instant::now() >= deadline {
+                return Err(ContainerError::Deployment(format!("Timed out waiting for {}", what)));
+            }
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+
+    async fn rsync(&self, source: Client, target: Client, claim: PersistentVolumeClaim) -> ContainerResult<()> {
+        let namespace = claim.namespace().unwrap_or_default();
+        let name = format!("sirsi-rsync-{}", claim.name_any());
+        let claims: Api<PersistentVolumeClaim> = Api::namespaced(target.clone(), &namespace);
+        if claims.get_opt(&claim.name_any()).await.map_err(|e| migration_error("read the target claim", e))?.is_none() {
+            claims
+                .create(&PostParams::default(), &claim)
+                .await
+                .map_err(|e| migration_error("create the target claim", e))?;
+        }
+
+        let password = uuid::Uuid::new_v4().simple().to_string();
+        let labels = serde_json::json!({ "app.kubernetes.io/name": name, MANAGED_BY_LABEL: MANAGED_BY_VALUE });
+        let volume = |read_only: bool| {
+            serde_json::json!([{ "name": "data", "persistentVolumeClaim": { "claimName": claim.name_any(), "readOnly": read_only } }])
+        };
+        let daemon: Pod = serde_json::from_value(serde_json::json!({
+            "metadata": { "name": name, "namespace": namespace, "labels": labels },
+            "spec": {
+                "containers": [{
+                    "name": "rsync",
+                    "image": self.image,
+                    "command": ["sh", "-c", concat!(
+                        "printf '[data]\\npath = /data\\nread only = false\\nuid = 0\\ngid = 0\\n",
+                        "auth users = sirsi\\nsecrets file = /tmp/rsyncd.secrets\\n' > /tmp/rsyncd.conf",
+                        " && echo \"sirsi:$RSYNC_PASSWORD\" > /tmp/rsyncd.secrets && chmod 600 /tmp/rsyncd.secrets",
+                        " && exec rsync --daemon --no-detach --port=873 --config=/tmp/rsyncd.conf",
+                    )],
+                    "env": [{ "name": "RSYNC_PASSWORD", "value": password }],
+                    "ports": [{ "containerPort": 873 }],
+                    "volumeMounts": [{ "name": "data", "mountPath": "/data" }],
+                }],
+                "volumes": volume(false),
+            },
+        }))
+        .map_err(|e| migration_error("build the rsync daemon", e))?;
+        let service: Service = serde_json::from_value(serde_json::json!({
+            "metadata": { "name": name, "namespace": namespace, "labels": labels },
+            "spec": { "type": "LoadBalancer", "selector": labels, "ports": [{ "port": 873, "targetPort": 873 }] },
+        }))
+        .map_err(|e| migration_error("build the rsync service", e))?;
+
+        let pods: Api<Pod> = Api::namespaced(target.clone(), &namespace);
+        let services: Api<Service> = Api::namespaced(target, &namespace);
+        let jobs: Api<Job> = Api::namespaced(source.clone(), &namespace);
+        let result = async {
+            pods.create(&PostParams::default(), &daemon).await.map_err(|e| migration_error("start the rsync daemon", e))?;
+            services.create(&PostParams::default(), &service).await.map_err(|e| migration_error("expose the rsync daemon", e))?;
+            let address = self
+                .until("the rsync service address", || {
+                    let services = services.clone();
+                    let name = name.clone();
+                    async move {
+                        let service = services.get(&name).await.map_err(|e| migration_error("read the rsync service", e))?;
+                        let ingress = service.status.and_then(|s| s.load_balancer).and_then(|lb| lb.ingress).unwrap_or_default();
+                        Ok(ingress.into_iter().find_map(|i| i.ip.or(i.hostname)))
+                    }
+                })
+                .await?;
+
+            // A ReadWriteOnce claim still mounted on the source can only be read from the same node
+            let source_pods = Api::<Pod>::namespaced(source, &namespace)
+                .list(&ListParams::default())
+                .await
+                .map_err(|e| migration_error("list source pods", e))?;
+            let node = source_pods.items.into_iter().find_map(|pod| {
+                let spec = pod.spec?;
+                let mounted = spec
+                    .volumes
+                    .iter()
+                    .flatten()
+                    .any(|v| v.persistent_volume_claim.as_ref().is_some_and(|c| c.claim_name == claim.name_any()));
+                mounted.then_some(spec.node_name).flatten()
+            });
+            let job: Job = serde_json::from_value(serde_json::json!({
+                "metadata": { "name": name, "namespace": namespace, "labels": labels },
+                "spec": {
+                    "backoffLimit": 3,
+                    "template": {
+                        "metadata": { "labels": labels },
+                        "spec": {
+                            "restartPolicy": "Never",
+                            "nodeName": node,
+                            "containers": [{
+                                "name": "rsync",
+                                "image": self.image,
+                                "command": ["rsync", "-a", "--delete", "/data/", format!("rsync://sirsi@{}:873/data/", address)],
+                                "env": [{ "name": "RSYNC_PASSWORD", "value": password }],
+                                "volumeMounts": [{ "name": "data", "mountPath": "/data", "readOnly": true }],
+                            }],
+                            "volumes": volume(true),
+                        },
+                    },
+                },
+            }))
+            .map_err(|e| migration_error("build the rsync job", e))?;
+            jobs.create(&PostParams::default(), &job).await.map_err(|e| migration_error("start the rsync job", e))?;
+            self.until("the rsync job", || {
+                let jobs = jobs.clone();
+                let name = name.clone();
+                async move {
+                    let status = jobs.get(&name).await.map_err(|e| migration_error("read the rsync job", e))?.status.unwrap_or_default();
+                    if status.succeeded.unwrap_or_default() > 0 {
+                        return Ok(Some(()));
+                    }
+                    if status.failed.unwrap_or_default() > 3 {
+                        return Err(ContainerError::Deployment(format!("Rsync job {} failed", name)));
+                    }
+                    Ok(None)
+                }
+            })
+            .await
+        }
+        .await;
+
+        let background = DeleteParams::background();
+        for outcome in [
+            jobs.delete(&name, &background).await.map(|_| ()),
+            pods.delete(&name, &background).await.map(|_| ()),
+            services.delete(&name, &background).await.map(|_| ()),
+        ] {
+            match outcome {
+                Ok(()) | Err(kube::Error::Api(ErrorResponse { code: 404, .. })) => {}
+                Err(e) => warn!("Failed to clean up rsync transfer {}: {}", name, e),
+            }
+        }
+        result
+    }
+
+    async fn restore_snapshot(
+        &self,
+        source: Client,
+        target: Client,
+        mut claim: PersistentVolumeClaim,
+        snapshot_class: &str,
+    ) -> ContainerResult<()> {
+        let namespace = claim.namespace().unwrap_or_default();
+        let claims: Api<PersistentVolumeClaim> = Api::namespaced(target.clone(), &namespace);
+        // A snapshot only fills a claim when it is created, so an existing one is already restored
+        if claims.get_opt(&claim.name_any()).await.map_err(|e| migration_error("read the target claim", e))?.is_some() {
+            return Ok(());
+        }
+        let snapshot_kind =
+            ApiResource::from_gvk_with_plural(&GroupVersionKind::gvk("snapshot.storage.k8s.io", "v1", "VolumeSnapshot"), "volumesnapshots");
+        let content_kind = ApiResource::from_gvk_with_plural(
+            &GroupVersionKind::gvk("snapshot.storage.k8s.io", "v1", "VolumeSnapshotContent"),
+            "volumesnapshotcontents",
+        );
+        let name = format!("sirsi-migrate-{}", claim.name_any());
+
+        // The source snapshot is kept: with a Delete policy, removing it would take the data the
+        // target is restoring from along with it
+        let source_snapshots: Api<DynamicObject> = Api::namespaced_with(source.clone(), &namespace, &snapshot_kind);
+        if source_snapshots.get_opt(&name).await.map_err(|e| migration_error("read the source snapshot", e))?.is_none() {
+            let snapshot = DynamicObject::new(&name, &snapshot_kind).within(&namespace).data(serde_json::json!({
+                "spec": { "volumeSnapshotClassName": snapshot_class, "source": { "persistentVolumeClaimName": claim.name_any() } },
+            }));
+            source_snapshots
+                .create(&PostParams::default(), &snapshot)
+                .await
+                .map_err(|e| migration_error("snapshot the source claim", e))?;
+        }
+        let content_name = self
+            .until("the source snapshot", || {
+                let snapshots = source_snapshots.clone();
+                let name = name.clone();
+                async move {
+                    let snapshot = snapshots.get(&name).await.map_err(|e| migration_error("read the source snapshot", e))?;
+                    let ready = snapshot.data.pointer("/status/readyToUse").and_then(Value::as_bool).unwrap_or_default();
+                    let content = snapshot.data.pointer("/status/boundVolumeSnapshotContentName").and_then(Value::as_str);
+                    Ok(content.filter(|_| ready).map(str::to_string))
+                }
+            })
+            .await?;
+        let content = Api::<DynamicObject>::all_with(source, &content_kind)
+            .get(&content_name)
+            .await
+            .map_err(|e| migration_error("read the source snapshot content", e))?;
+        let driver = content.data.pointer("/spec/driver").cloned().unwrap_or_default();
+        let handle = content.data.pointer("/status/snapshotHandle").cloned().unwrap_or_default();
+
+        // Retained, since the backend snapshot belongs to the source
+        let target_content_name = format!("{}-{}", name, namespace);
+        let target_content = DynamicObject::new(&target_content_name, &content_kind).data(serde_json::json!({
+            "spec": {
+                "deletionPolicy": "Retain",
+                "driver": driver,
+                "source": { "snapshotHandle": handle },
+                "volumeSnapshotClassName": snapshot_class,
+                "volumeSnapshotRef": { "name": name, "namespace": namespace },
+            },
+        }));
+        let target_snapshot = DynamicObject::new(&name, &snapshot_kind)
+            .within(&namespace)
+            .data(serde_json::json!({ "spec": { "source": { "volumeSnapshotContentName": target_content_name } } }));
+        let params = PatchParams::apply("sirsi-container-manager").force();
+        Api::<DynamicObject>::all_with(target.clone(), &content_kind)
+            .patch(&target_content_name, &params, &Patch::Apply(&target_content))
+            .await
+            .map_err(|e| migration_error("import the snapshot", e))?;
+        Api::<DynamicObject>::namespaced_with(target, &namespace, &snapshot_kind)
+            .patch(&name, &params, &Patch::Apply(&target_snapshot))
+            .await
+            .map_err(|e| migration_error("import the snapshot", e))?;
+
+        claim.spec.get_or_insert_with(Default::default).data_source = Some(TypedLocalObjectReference {
+            api_group: Some("snapshot.storage.k8s.io".to_string()),
+            kind: "VolumeSnapshot".to_string(),
+            name,
+        });
+        claims
+            .create(&PostParams::default(), &claim)
+            .await
+            .map_err(|e| migration_error("restore the target claim", e))?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl VolumeTransfer for KubeVolumeTransfer {
+    async fn sync(&self, source_kubeconfig: &str, target_kubeconfig: &str, claim: &Value, method: &TransferMethod) -> ContainerResult<()> {
+        let source = KubeClusterAccess::client(source_kubeconfig).await?;
+        let target = KubeClusterAccess::client(target_kubeconfig).await?;
+        let claim: PersistentVolumeClaim =
+            serde_json::from_value(claim.clone()).map_err(|e| ContainerError::Validation(format!("Invalid claim: {}", e)))?;
+        match method {
+            TransferMethod::Rsync => self.rsync(source, target, claim).await,
+            TransferMethod::Snapshot { snapshot_class } => self.restore_snapshot(source, target, claim, snapshot_class).await,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
 
     #[tokio::test]
     async fn test_deployment_lifecycle() {
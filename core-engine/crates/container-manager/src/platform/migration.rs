@@ -0,0 +1,987 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+use super::cluster::KubeconfigSource;
+use super::drain::{WorkloadKind, WorkloadRef};
+use crate::error::{ContainerError, ContainerResult};
+
+// Objects the control plane or a controller creates on its own; the target makes its own
+const GENERATED_KINDS: &[&str] = &["Pod", "ReplicaSet", "ControllerRevision", "Event", "Endpoints", "EndpointSlice", "Lease"];
+// `Kind/name` of the per-namespace objects every cluster creates
+const GENERATED_OBJECTS: &[&str] = &["ConfigMap/kube-root-ca.crt", "ServiceAccount/default"];
+const GENERATED_METADATA: &[&str] = &[
+    "uid",
+    "resourceVersion",
+    "generation",
+    "creationTimestamp",
+    "deletionTimestamp",
+    "deletionGracePeriodSeconds",
+    "managedFields",
+    "selfLink",
+    "ownerReferences",
+];
+const GENERATED_ANNOTATIONS: &[&str] = &["kubectl.kubernetes.io/last-applied-configuration", "deployment.kubernetes.io/revision"];
+const GENERATED_ANNOTATION_PREFIXES: &[&str] = &["pv.kubernetes.io/", "volume.kubernetes.io/", "volume.beta.kubernetes.io/"];
+const JOB_CONTROLLER_LABELS: &[&str] = &["controller-uid", "batch.kubernetes.io/controller-uid"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MigrationOptions {
+    // `Kind` or `Kind/name`, left behind on top of what the cluster generates
+    pub exclude: Vec<String>,
+    // Stops the source workloads before the final data sync, so no writes are lost
+    pub scale_down_source: bool,
+    // Storage classes whose volumes can be snapshot-restored on the target, to the
+    // VolumeSnapshotClass to use; other volumes are copied with rsync
+    pub snapshot_classes: HashMap<String, String>,
+    // For the source to scale down, and later for the target workloads to become ready
+    pub wait_timeout: Duration,
+}
+
+impl Default for MigrationOptions {
+    fn default() -> Self {
+        Self {
+            exclude: Vec::new(),
+            scale_down_source: false,
+            snapshot_classes: HashMap::new(),
+            wait_timeout: Duration::from_secs(600),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum TransferMethod {
+    Rsync,
+    Snapshot { snapshot_class: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkloadReadiness {
+    pub workload: WorkloadRef,
+    pub desired: i32,
+    pub replicas: i32,
+    pub ready: i32,
+}
+
+// Namespace export and import calls a migration needs; objects are plain API JSON
+#[async_trait]
+pub trait MigrationApi: Send + Sync {
+    // Every namespaced object in the namespace, each with `apiVersion` and `kind` set
+    async fn export_namespace(&self, kubeconfig: &str, namespace: &str) -> ContainerResult<Vec<Value>>;
+    // `None` for built-in kinds
+    async fn custom_resource_definition(&self, kubeconfig: &str, api_version: &str, kind: &str) -> ContainerResult<Option<Value>>;
+    async fn ensure_namespace(&self, kubeconfig: &str, namespace: &str) -> ContainerResult<()>;
+    // Server-side apply, so applying again on resume is harmless
+    async fn apply(&self, kubeconfig: &str, object: &Value) -> ContainerResult<()>;
+    async fn scale(&self, kubeconfig: &str, workload: &WorkloadRef, replicas: i32) -> ContainerResult<()>;
+    async fn readiness(&self, kubeconfig: &str, namespace: &str) -> ContainerResult<Vec<WorkloadReadiness>>;
+}
+
+// Copies a claim's data between clusters. Creates the target claim from `claim` if it does
+// not exist; with rsync, repeat syncs only move what changed.
+#[async_trait]
+pub trait VolumeTransfer: Send + Sync {
+    async fn sync(&self, source_kubeconfig: &str, target_kubeconfig: &str, claim: &Value, method: &TransferMethod) -> ContainerResult<()>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MigratedResource {
+    pub api_version: String,
+    pub kind: String,
+    pub name: String,
+    pub manifest: Value,
+    pub applied: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VolumeSync {
+    pub claim: String,
+    pub method: TransferMethod,
+    pub passes: u32,
+    pub synced: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScaledWorkload {
+    pub workload: WorkloadRef,
+    pub replicas: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KindCount {
+    pub kind: String,
+    pub source: usize,
+    pub target: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerificationReport {
+    pub object_counts: Vec<KindCount>,
+    pub workloads: Vec<WorkloadReadiness>,
+    pub problems: Vec<String>,
+    pub passed: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum MigrationStep {
+    Export,
+    Apply,
+    FinalSync,
+    Cutover,
+    Verify,
+    Done,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MigrationStatus {
+    Running,
+    Completed,
+    // Stopped at `step`; resuming carries on from there
+    Failed,
+    Abandoned,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MigrationRecord {
+    pub id: String,
+    pub source_cluster: String,
+    pub target_cluster: String,
+    pub namespace: String,
+    pub options: MigrationOptions,
+    pub status: MigrationStatus,
+    pub step: MigrationStep,
+    pub error: Option<String>,
+    // In apply order
+    pub resources: Vec<MigratedResource>,
+    pub volumes: Vec<VolumeSync>,
+    // Source workloads scaled down for the final sync, with their original replicas
+    pub source_scaled: Vec<ScaledWorkload>,
+    pub report: Option<VerificationReport>,
+    pub started_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl MigrationRecord {
+    pub fn is_active(&self) -> bool {
+        matches!(self.status, MigrationStatus::Running | MigrationStatus::Failed)
+    }
+}
+
+#[async_trait]
+pub trait MigrationStore: Send + Sync {
+    async fn save_migration(&self, record: &MigrationRecord) -> ContainerResult<()>;
+    async fn get_migration(&self, id: &str) -> ContainerResult<Option<MigrationRecord>>;
+    async fn list_migrations(&self) -> ContainerResult<Vec<MigrationRecord>>;
+}
+
+#[derive(Default)]
+pub struct InMemoryMigrationStore {
+    migrations: RwLock<HashMap<String, MigrationRecord>>,
+}
+
+impl InMemoryMigrationStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl MigrationStore for InMemoryMigrationStore {
+    async fn save_migration(&self, record: &MigrationRecord) -> ContainerResult<()> {
+        self.migrations.write().await.insert(record.id.clone(), record.clone());
+        Ok(())
+    }
+
+    async fn get_migration(&self, id: &str) -> ContainerResult<Option<MigrationRecord>> {
+        Ok(self.migrations.read().await.get(id).cloned())
+    }
+
+    async fn list_migrations(&self) -> ContainerResult<Vec<MigrationRecord>> {
+        let mut records: Vec<MigrationRecord> = self.migrations.read().await.values().cloned().collect();
+        records.sort_by_key(|r| r.started_at);
+        Ok(records)
+    }
+}
+
+fn text<'a>(object: &'a Value, pointer: &str) -> &'a str {
+    object.pointer(pointer).and_then(Value::as_str).unwrap_or_default()
+}
+
+fn remove(object: &mut Value, pointer: &str, key: &str) {
+    if let Some(map) = object.pointer_mut(pointer).and_then(Value::as_object_mut) {
+        map.remove(key);
+    }
+}
+
+fn is_excluded(object: &Value, exclude: &[String]) -> bool {
+    let kind = text(object, "/kind");
+    let qualified = format!("{}/{}", kind, text(object, "/metadata/name"));
+    let owned = object.pointer("/metadata/ownerReferences").and_then(Value::as_array).is_some_and(|o| !o.is_empty());
+    owned
+        || GENERATED_KINDS.contains(&kind)
+        || GENERATED_OBJECTS.contains(&qualified.as_str())
+        || (kind == "Secret" && text(object, "/type") == "kubernetes.io/service-account-token")
+        || exclude.iter().any(|e| *e == kind || *e == qualified)
+}
+
+// Strips what the source cluster filled in, leaving what a user would have applied
+pub fn sanitize(mut object: Value) -> Value {
+    if let Some(object) = object.as_object_mut() {
+        object.remove("status");
+    }
+    for field in GENERATED_METADATA {
+        remove(&mut object, "/metadata", field);
+    }
+    if let Some(annotations) = object.pointer_mut("/metadata/annotations").and_then(Value::as_object_mut) {
+        annotations.retain(|key, _| {
+            !GENERATED_ANNOTATIONS.contains(&key.as_str()) && !GENERATED_ANNOTATION_PREFIXES.iter().any(|p| key.starts_with(p))
+        });
+    }
+    match text(&object, "/kind") {
+        "Service" => {
+            // Headless services keep `None`; allocated IPs and node ports belong to the source
+            if text(&object, "/spec/clusterIP") != "None" {
+                remove(&mut object, "/spec", "clusterIP");
+                remove(&mut object, "/spec", "clusterIPs");
+            }
+            remove(&mut object, "/spec", "healthCheckNodePort");
+            if let Some(ports) = object.pointer_mut("/spec/ports").and_then(Value::as_array_mut) {
+                for port in ports.iter_mut().filter_map(Value::as_object_mut) {
+                    port.remove("nodePort");
+                }
+            }
+        }
+        "PersistentVolumeClaim" => remove(&mut object, "/spec", "volumeName"),
+        "ServiceAccount" => remove(&mut object, "", "secrets"),
+        "Job" if object.pointer("/spec/manualSelector") != Some(&Value::Bool(true)) => {
+            remove(&mut object, "/spec", "selector");
+            for label in JOB_CONTROLLER_LABELS {
+                remove(&mut object, "/spec/template/metadata/labels", label);
+            }
+        }
+        _ => {}
+    }
+    object
+}
+
+// Configuration before the things that consume it, workloads before what routes to them, and
+// custom resources last, after the operators that reconcile them
+fn apply_rank(kind: &str) -> u8 {
+    match kind {
+        "CustomResourceDefinition" => 0,
+        "ServiceAccount" | "Role" | "RoleBinding" | "ResourceQuota" | "LimitRange" | "NetworkPolicy" => 1,
+        "ConfigMap" | "Secret" => 2,
+        "Service" => 3,
+        "PersistentVolumeClaim" => 4,
+        "Deployment" | "StatefulSet" | "DaemonSet" | "ReplicationController" | "Job" | "CronJob" => 5,
+        "Ingress" | "PodDisruptionBudget" | "HorizontalPodAutoscaler" => 6,
+        _ => 7,
+    }
+}
+
+// The exported objects to recreate, sanitized and in apply order
+pub fn plan_resources(exported: Vec<Value>, exclude: &[String]) -> Vec<MigratedResource> {
+    let mut resources: Vec<MigratedResource> = exported
+        .into_iter()
+        .filter(|object| !is_excluded(object, exclude))
+        .map(|object| {
+            let object = sanitize(object);
+            MigratedResource {
+                api_version: text(&object, "/apiVersion").to_string(),
+                kind: text(&object, "/kind").to_string(),
+                name: text(&object, "/metadata/name").to_string(),
+                manifest: object,
+                applied: false,
+            }
+        })
+        .collect();
+    resources.sort_by(|a, b| (apply_rank(&a.kind), &a.kind, &a.name).cmp(&(apply_rank(&b.kind), &b.kind, &b.name)));
+    resources
+}
+
+fn scalable(resource: &MigratedResource) -> Option<(WorkloadRef, i32)> {
+    let kind = match resource.kind.as_str() {
+        "Deployment" => WorkloadKind::Deployment,
+        "StatefulSet" => WorkloadKind::StatefulSet,
+        _ => return None,
+    };
+    let namespace = text(&resource.manifest, "/metadata/namespace").to_string();
+    let replicas = resource.manifest.pointer("/spec/replicas").and_then(Value::as_i64).unwrap_or(1) as i32;
+    Some((WorkloadRef { namespace, kind, name: resource.name.clone() }, replicas))
+}
+
+// Target workloads are created stopped, and only started at cutover once their data is there
+fn held(resource: &MigratedResource) -> Value {
+    let mut manifest = resource.manifest.clone();
+    match resource.kind.as_str() {
+        "Deployment" | "StatefulSet" => manifest["spec"]["replicas"] = Value::from(0),
+        "CronJob" => manifest["spec"]["suspend"] = Value::Bool(true),
+        _ => {}
+    }
+    manifest
+}
+
+// Moves a namespace between clusters: config, then volumes and workloads, then a cutover that
+// starts the target's workloads. Progress is saved after every step so a failed migration
+// resumes where it stopped.
+pub struct NamespaceMigrator {
+    api: Arc<dyn MigrationApi>,
+    transfer: Arc<dyn VolumeTransfer>,
+    kubeconfigs: Arc<dyn KubeconfigSource>,
+    store: Arc<dyn MigrationStore>,
+    poll_interval: Duration,
+}
+
+impl NamespaceMigrator {
+    pub fn new(api: Arc<dyn MigrationApi>, transfer: Arc<dyn VolumeTransfer>, kubeconfigs: Arc<dyn KubeconfigSource>) -> Self {
+        Self {
+            api,
+            transfer,
+            kubeconfigs,
+            store: Arc::new(InMemoryMigrationStore::new()),
+            poll_interval: Duration::from_secs(5),
+        }
+    }
+
+    pub fn with_store(mut self, store: Arc<dyn MigrationStore>) -> Self {
+        self.store = store;
+        self
+    }
+
+    pub fn with_poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    pub async fn migrate_namespace(
+        &self,
+        source_cluster: &str,
+        target_cluster: &str,
+        namespace: &str,
+        options: MigrationOptions,
+    ) -> ContainerResult<MigrationRecord> {
+        if source_cluster == target_cluster {
+            return Err(ContainerError::Validation("Source and target clusters must differ".into()));
+        }
+        let active = self.store.list_migrations().await?;
+        if let Some(existing) = active.iter().find(|r| r.is_active() && r.namespace == namespace && r.target_cluster == target_cluster) {
+            return Err(ContainerError::Validation(format!(
+                "Namespace {} is already being migrated to {} by {}",
+                namespace, target_cluster, existing.id
+            )));
+        }
+        let now = Utc::now();
+        let record = MigrationRecord {
+            id: uuid::Uuid::new_v4().to_string(),
+            source_cluster: source_cluster.to_string(),
+            target_cluster: target_cluster.to_string(),
+            namespace: namespace.to_string(),
+            options,
+            status: MigrationStatus::Running,
+            step: MigrationStep::Export,
+            error: None,
+            resources: Vec::new(),
+            volumes: Vec::new(),
+            source_scaled: Vec::new(),
+            report: None,
+            started_at: now,
+            updated_at: now,
+        };
+        self.store.save_migration(&record).await?;
+        info!(namespace, source = source_cluster, target = target_cluster, migration = %record.id, "Migrating namespace");
+        self.run(record).await
+    }
+
+    pub async fn get_migration(&self, id: &str) -> ContainerResult<MigrationRecord> {
+        self.store
+            .get_migration(id)
+            .await?
+            .ok_or_else(|| ContainerError::NotFound(format!("Migration {} not found", id)))
+    }
+
+    pub async fn resume_migration(&self, id: &str) -> ContainerResult<MigrationRecord> {
+        let mut record = self.get_migration(id).await?;
+        if !record.is_active() {
+            return Err(ContainerError::Validation(format!("Migration {} is {:?}", id, record.status)));
+        }
+        info!(migration = id, step = ?record.step, "Resuming migration");
+        record.status = MigrationStatus::Running;
+        record.error = None;
+        self.run(record).await
+    }
+
+    // Gives up on a migration, starting the source workloads again if they were stopped.
+    // What was already created on the target is left for the caller to remove.
+    pub async fn abandon_migration(&self, id: &str) -> ContainerResult<MigrationRecord> {
+        let mut record = self.get_migration(id).await?;
+        if !record.is_active() {
+            return Err(ContainerError::Validation(format!("Migration {} is {:?}", id, record.status)));
+        }
+        if !record.source_scaled.is_empty() {
+            let source = self.kubeconfigs.kubeconfig(&record.source_cluster).await?;
+            for scaled in &record.source_scaled {
+                self.api.scale(&source, &scaled.workload, scaled.replicas).await?;
+            }
+            record.source_scaled.clear();
+        }
+        record.status = MigrationStatus::Abandoned;
+        self.checkpoint(&mut record).await?;
+        Ok(record)
+    }
+
+    async fn checkpoint(&self, record: &mut MigrationRecord) -> ContainerResult<()> {
+        record.updated_at = Utc::now();
+        self.store.save_migration(record).await
+    }
+
+    async fn run(&self, mut record: MigrationRecord) -> ContainerResult<MigrationRecord> {
+        match self.advance(&mut record).await {
+            Ok(()) => {
+                record.status = MigrationStatus::Completed;
+                info!(migration = %record.id, namespace = %record.namespace, "Namespace migrated");
+            }
+            Err(e) => {
+                warn!(migration = %record.id, step = ?record.step, "Migration stopped: {}", e);
+                record.status = MigrationStatus::Failed;
+                record.error = Some(e.to_string());
+            }
+        }
+        self.checkpoint(&mut record).await?;
+        Ok(record)
+    }
+
+    async fn advance(&self, record: &mut MigrationRecord) -> ContainerResult<()> {
+        let source = self.kubeconfigs.kubeconfig(&record.source_cluster).await?;
+        let target = self.kubeconfigs.kubeconfig(&record.target_cluster).await?;
+        if record.step == MigrationStep::Export {
+            self.export(record, &source, &target).await?;
+            record.step = MigrationStep::Apply;
+            self.checkpoint(record).await?;
+        }
+        if record.step == MigrationStep::Apply {
+            self.apply(record, &source, &target).await?;
+            record.step = MigrationStep::FinalSync;
+            self.checkpoint(record).await?;
+        }
+        if record.step == MigrationStep::FinalSync {
+            self.final_sync(record, &source, &target).await?;
+            record.step = MigrationStep::Cutover;
+            self.checkpoint(record).await?;
+        }
+        if record.step == MigrationStep::Cutover {
+            for resource in record.resources.iter().filter(|r| r.manifest != held(r)) {
+                self.api.apply(&target, &resource.manifest).await?;
+            }
+            record.step = MigrationStep::Verify;
+            self.checkpoint(record).await?;
+        }
+        if record.step == MigrationStep::Verify {
+            let report = self.verify(record, &target).await?;
+            let passed = report.passed;
+            let problems = report.problems.join("; ");
+            record.report = Some(report);
+            if !passed {
+                return Err(ContainerError::Deployment(format!("Verification failed: {}", problems)));
+            }
+            record.step = MigrationStep::Done;
+        }
+        Ok(())
+    }
+
+    async fn export(&self, record: &mut MigrationRecord, source: &str, target: &str) -> ContainerResult<()> {
+        let exported = self.api.export_namespace(source, &record.namespace).await?;
+        let mut resources = plan_resources(exported, &record.options.exclude);
+
+        // Definitions for the namespace's custom resources, unless the target has its own
+        let kinds: BTreeSet<(String, String)> = resources.iter().map(|r| (r.api_version.clone(), r.kind.clone())).collect();
+        let mut definitions = Vec::new();
+        for (api_version, kind) in kinds {
+            let Some(definition) = self.api.custom_resource_definition(source, &api_version, &kind).await? else { continue };
+            if self.api.custom_resource_definition(target, &api_version, &kind).await?.is_none() {
+                definitions.extend(plan_resources(vec![definition], &[]));
+            }
+        }
+        resources.splice(0..0, definitions);
+
+        record.volumes = resources
+            .iter()
+            .filter(|r| r.kind == "PersistentVolumeClaim")
+            .map(|claim| {
+                let storage_class = text(&claim.manifest, "/spec/storageClassName");
+                let method = match record.options.snapshot_classes.get(storage_class) {
+                    Some(snapshot_class) => TransferMethod::Snapshot { snapshot_class: snapshot_class.clone() },
+                    None => TransferMethod::Rsync,
+                };
+                VolumeSync { claim: claim.name.clone(), method, passes: 0, synced: false }
+            })
+            .collect();
+        record.resources = resources;
+        Ok(())
+    }
+
+    // A snapshot is a one-off copy, so with the source being stopped it waits for the final sync
+    fn sync_now(&self, record: &MigrationRecord, volume: &VolumeSync) -> bool {
+        !(record.options.scale_down_source && matches!(volume.method, TransferMethod::Snapshot { .. }))
+    }
+
+    async fn apply(&self, record: &mut MigrationRecord, source: &str, target: &str) -> ContainerResult<()> {
+        self.api.ensure_namespace(target, &record.namespace).await?;
+        for index in 0..record.resources.len() {
+            let resource = &record.resources[index];
+            if resource.applied {
+                continue;
+            }
+            if resource.kind == "PersistentVolumeClaim" {
+                let volume = record.volumes.iter().position(|v| v.claim == resource.name);
+                if let Some(volume) = volume.filter(|v| self.sync_now(record, &record.volumes[*v]) && record.volumes[*v].passes == 0) {
+                    self.transfer.sync(source, target, &resource.manifest, &record.volumes[volume].method).await?;
+                    let volume = &mut record.volumes[volume];
+                    volume.passes += 1;
+                    volume.synced = !record.options.scale_down_source;
+                }
+            } else {
+                self.api.apply(target, &held(resource)).await?;
+            }
+            record.resources[index].applied = true;
+            self.checkpoint(record).await?;
+        }
+        Ok(())
+    }
+
+    async fn final_sync(&self, record: &mut MigrationRecord, source: &str, target: &str) -> ContainerResult<()> {
+        if !record.options.scale_down_source {
+            return Ok(());
+        }
+        if record.source_scaled.is_empty() {
+            record.source_scaled = record
+                .resources
+                .iter()
+                .filter_map(scalable)
+                .map(|(workload, replicas)| ScaledWorkload { workload, replicas })
+                .collect();
+            self.checkpoint(record).await?;
+        }
+        for scaled in &record.source_scaled {
+            self.api.scale(source, &scaled.workload, 0).await?;
+        }
+        self.wait_for(source, &record.namespace, record.options.wait_timeout, |states| {
+            record.source_scaled.iter().all(|s| {
+                states.iter().find(|w| w.workload == s.workload).is_none_or(|w| w.replicas == 0 && w.ready == 0)
+            })
+        })
+        .await
+        .map_err(|_| ContainerError::Deployment("Source workloads did not stop before the final sync".into()))?;
+
+        for index in 0..record.volumes.len() {
+            if record.volumes[index].synced {
+                continue;
+            }
+            let claim = record
+                .resources
+                .iter()
+                .find(|r| r.kind == "PersistentVolumeClaim" && r.name == record.volumes[index].claim)
+                .map(|r| r.manifest.clone())
+                .ok_or_else(|| ContainerError::Internal(format!("Claim {} is not in the plan", record.volumes[index].claim)))?;
+            self.transfer.sync(source, target, &claim, &record.volumes[index].method).await?;
+            record.volumes[index].passes += 1;
+            record.volumes[index].synced = true;
+            self.checkpoint(record).await?;
+        }
+        Ok(())
+    }
+
+    async fn wait_for<F>(&self, kubeconfig: &str, namespace: &str, timeout: Duration, done: F) -> ContainerResult<Vec<WorkloadReadiness>>
+    where
+        F: Fn(&[WorkloadReadiness]) -> bool,
+    {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let states = self.api.readiness(kubeconfig, namespace).await?;
+            if done(&states) {
+                return Ok(states);
+            }
+            if tokio::time::Instant::now() >= deadline {
+                return Err(ContainerError::Deployment(format!("Timed out waiting for workloads in {}", namespace)));
+            }
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+
+    async fn verify(&self, record: &MigrationRecord, target: &str) -> ContainerResult<VerificationReport> {
+        let expected: Vec<WorkloadRef> = record.resources.iter().filter_map(scalable).map(|(w, _)| w).collect();
+        let all_ready = |states: &[WorkloadReadiness]| {
+            expected
+                .iter()
+                .all(|w| states.iter().any(|s| s.workload == *w && s.ready >= s.desired))
+        };
+        let workloads = match self.wait_for(target, &record.namespace, record.options.wait_timeout, all_ready).await {
+            Ok(states) => states,
+            Err(_) => self.api.readiness(target, &record.namespace).await?,
+        };
+
+        let migrated = plan_resources(self.api.export_namespace(target, &record.namespace).await?, &record.options.exclude);
+        let mut counts: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+        for resource in record.resources.iter().filter(|r| r.kind != "CustomResourceDefinition") {
+            counts.entry(resource.kind.clone()).or_default().0 += 1;
+        }
+        for resource in &migrated {
+            counts.entry(resource.kind.clone()).or_default().1 += 1;
+        }
+        let object_counts: Vec<KindCount> =
+            counts.into_iter().map(|(kind, (source, target))| KindCount { kind, source, target }).collect();
+
+        let mut problems: Vec<String> = object_counts
+            .iter()
+            .filter(|c| c.target < c.source)
+            .map(|c| format!("{} of {} {} objects on the target", c.target, c.source, c.kind))
+            .collect();
+        for workload in &expected {
+            match workloads.iter().find(|s| s.workload == *workload) {
+                Some(state) if state.ready >= state.desired => {}
+                Some(state) => problems.push(format!("{} has {}/{} replicas ready", workload.name, state.ready, state.desired)),
+                None => problems.push(format!("{} is missing on the target", workload.name)),
+            }
+        }
+        Ok(VerificationReport { passed: problems.is_empty(), object_counts, workloads, problems })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use serde_json::json;
+
+    use super::*;
+
+    fn object(api_version: &str, kind: &str, name: &str, extra: Value) -> Value {
+        let mut object = json!({
+            "apiVersion": api_version,
+            "kind": kind,
+            "metadata": {
+                "name": name,
+                "namespace": "shop",
+                "uid": "5c6f1e9a",
+                "resourceVersion": "81723",
+                "creationTimestamp": "2024-03-01T10:00:00Z",
+                "managedFields": [{ "manager": "kubectl" }],
+                "annotations": { "kubectl.kubernetes.io/last-applied-configuration": "{}", "team": "payments" },
+            },
+            "status": { "phase": "Bound" },
+        });
+        if let (Some(base), Some(extra)) = (object.as_object_mut(), extra.as_object()) {
+            base.extend(extra.clone());
+        }
+        object
+    }
+
+    fn namespace_objects() -> Vec<Value> {
+        vec![
+            object("apps/v1", "Deployment", "web", json!({ "spec": { "replicas": 3 } })),
+            object("v1", "Service", "web", json!({ "spec": { "clusterIP": "10.0.4.12", "clusterIPs": ["10.0.4.12"], "ports": [{ "port": 80, "nodePort": 31080 }] } })),
+            object("v1", "Service", "db", json!({ "spec": { "clusterIP": "None" } })),
+            object("v1", "PersistentVolumeClaim", "uploads", json!({ "spec": { "storageClassName": "ebs-gp3", "volumeName": "pvc-81c2" } })),
+            object("v1", "PersistentVolumeClaim", "scratch", json!({ "spec": { "storageClassName": "nfs", "volumeName": "pvc-e410" } })),
+            object("v1", "ConfigMap", "settings", json!({ "data": { "mode": "live" } })),
+            object("v1", "ConfigMap", "kube-root-ca.crt", json!({})),
+            object("v1", "Secret", "db-credentials", json!({ "type": "Opaque" })),
+            object("v1", "Secret", "legacy-token", json!({ "type": "kubernetes.io/service-account-token" })),
+            object("v1", "ServiceAccount", "default", json!({})),
+            object("networking.k8s.io/v1", "Ingress", "web", json!({})),
+            object("shop.sirsi.io/v1", "PaymentGateway", "stripe", json!({})),
+            object("v1", "Secret", "scratchpad", json!({ "type": "Opaque" })),
+            object("apps/v1", "ReplicaSet", "web-6d4f", json!({})),
+            object("v1", "Pod", "web-6d4f-x2k", json!({})),
+        ]
+    }
+
+    #[test]
+    fn test_sanitize_and_apply_order() {
+        let plan = plan_resources(namespace_objects(), &["Secret/scratchpad".to_string()]);
+        let order: Vec<String> = plan.iter().map(|r| format!("{}/{}", r.kind, r.name)).collect();
+        assert_eq!(
+            order,
+            vec![
+                "ConfigMap/settings",
+                "Secret/db-credentials",
+                "Service/db",
+                "Service/web",
+                "PersistentVolumeClaim/scratch",
+                "PersistentVolumeClaim/uploads",
+                "Deployment/web",
+                "Ingress/web",
+                "PaymentGateway/stripe",
+            ]
+        );
+
+        let find = |qualified: &str| plan.iter().find(|r| format!("{}/{}", r.kind, r.name) == qualified).unwrap().manifest.clone();
+        let web = find("Service/web");
+        assert!(web.get("status").is_none());
+        assert_eq!(web["metadata"], json!({ "name": "web", "namespace": "shop", "annotations": { "team": "payments" } }));
+        assert!(web["spec"].get("clusterIP").is_none());
+        assert_eq!(web["spec"]["ports"], json!([{ "port": 80 }]));
+        assert_eq!(find("Service/db")["spec"]["clusterIP"], "None");
+        assert!(find("PersistentVolumeClaim/uploads")["spec"].get("volumeName").is_none());
+
+        let job = sanitize(json!({
+            "kind": "Job",
+            "metadata": { "name": "backfill" },
+            "spec": {
+                "selector": { "matchLabels": { "controller-uid": "91ab" } },
+                "template": { "metadata": { "labels": { "controller-uid": "91ab", "job-name": "backfill" } } },
+            },
+        }));
+        assert!(job["spec"].get("selector").is_none());
+        assert_eq!(job["spec"]["template"]["metadata"]["labels"], json!({ "job-name": "backfill" }));
+    }
+
+    #[derive(Default)]
+    struct FakeClusters {
+        objects: Mutex<HashMap<String, Vec<Value>>>,
+        definitions: Mutex<HashMap<String, Vec<Value>>>,
+        applied: Mutex<Vec<String>>,
+        fail_apply: Mutex<Option<String>>,
+        transfers: Mutex<Vec<(String, TransferMethod, bool)>>,
+    }
+
+    impl FakeClusters {
+        fn upsert(&self, cluster: &str, object: Value) {
+            let mut objects = self.objects.lock().unwrap();
+            let objects = objects.entry(cluster.to_string()).or_default();
+            let key = |o: &Value| (text(o, "/kind").to_string(), text(o, "/metadata/name").to_string());
+            objects.retain(|o| key(o) != key(&object));
+            objects.push(object);
+        }
+
+        fn replicas(&self, cluster: &str, name: &str) -> i64 {
+            let objects = self.objects.lock().unwrap();
+            let deployment = objects[cluster].iter().find(|o| text(o, "/kind") == "Deployment" && text(o, "/metadata/name") == name);
+            deployment.unwrap()["spec"]["replicas"].as_i64().unwrap()
+        }
+    }
+
+    #[async_trait]
+    impl MigrationApi for FakeClusters {
+        async fn export_namespace(&self, kubeconfig: &str, _namespace: &str) -> ContainerResult<Vec<Value>> {
+            Ok(self.objects.lock().unwrap().get(kubeconfig).cloned().unwrap_or_default())
+        }
+
+        async fn custom_resource_definition(&self, kubeconfig: &str, api_version: &str, kind: &str) -> ContainerResult<Option<Value>> {
+            let group = api_version.split('/').next().unwrap_or_default();
+            let definitions = self.definitions.lock().unwrap();
+            Ok(definitions
+                .get(kubeconfig)
+                .and_then(|d| d.iter().find(|d| text(d, "/spec/group") == group && text(d, "/spec/names/kind") == kind))
+                .cloned())
+        }
+
+        async fn ensure_namespace(&self, _kubeconfig: &str, _namespace: &str) -> ContainerResult<()> {
+            Ok(())
+        }
+
+        async fn apply(&self, kubeconfig: &str, object: &Value) -> ContainerResult<()> {
+            let qualified = format!("{}/{}", text(object, "/kind"), text(object, "/metadata/name"));
+            if self.fail_apply.lock().unwrap().take_if(|name| *name == qualified).is_some() {
+                return Err(ContainerError::Platform(format!("admission webhook denied {}", qualified)));
+            }
+            self.applied.lock().unwrap().push(qualified);
+            if text(object, "/kind") == "CustomResourceDefinition" {
+                self.definitions.lock().unwrap().entry(kubeconfig.to_string()).or_default().push(object.clone());
+            } else {
+                self.upsert(kubeconfig, object.clone());
+            }
+            Ok(())
+        }
+
+        async fn scale(&self, kubeconfig: &str, workload: &WorkloadRef, replicas: i32) -> ContainerResult<()> {
+            let mut objects = self.objects.lock().unwrap();
+            for object in objects.get_mut(kubeconfig).into_iter().flatten() {
+                if text(object, "/metadata/name") == workload.name && text(object, "/kind") == "Deployment" {
+                    object["spec"]["replicas"] = Value::from(replicas);
+                }
+            }
+            Ok(())
+        }
+
+        // Every pod is ready the moment it is asked for
+        async fn readiness(&self, kubeconfig: &str, namespace: &str) -> ContainerResult<Vec<WorkloadReadiness>> {
+            let objects = self.objects.lock().unwrap();
+            Ok(objects
+                .get(kubeconfig)
+                .into_iter()
+                .flatten()
+                .filter(|o| text(o, "/kind") == "Deployment")
+                .map(|o| {
+                    let replicas = o["spec"]["replicas"].as_i64().unwrap_or(1) as i32;
+                    let name = text(o, "/metadata/name").to_string();
+                    WorkloadReadiness {
+                        workload: WorkloadRef { namespace: namespace.to_string(), kind: WorkloadKind::Deployment, name },
+                        desired: replicas,
+                        replicas,
+                        ready: replicas,
+                    }
+                })
+                .collect())
+        }
+    }
+
+    #[async_trait]
+    impl VolumeTransfer for FakeClusters {
+        async fn sync(&self, source: &str, target: &str, claim: &Value, method: &TransferMethod) -> ContainerResult<()> {
+            let source_stopped = self.replicas(source, "web") == 0;
+            self.transfers.lock().unwrap().push((text(claim, "/metadata/name").to_string(), method.clone(), source_stopped));
+            self.upsert(target, claim.clone());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_migration_resumes_after_failure_and_syncs_after_scale_down() {
+        let clusters = Arc::new(FakeClusters::default());
+        for object in namespace_objects() {
+            clusters.upsert("old", object);
+        }
+        let definition = json!({
+            "apiVersion": "apiextensions.k8s.io/v1",
+            "kind": "CustomResourceDefinition",
+            "metadata": { "name": "paymentgateways.shop.sirsi.io", "uid": "a1" },
+            "spec": { "group": "shop.sirsi.io", "names": { "kind": "PaymentGateway" } },
+        });
+        clusters.definitions.lock().unwrap().insert("old".into(), vec![definition]);
+        *clusters.fail_apply.lock().unwrap() = Some("Deployment/web".into());
+
+        let kubeconfigs = HashMap::from([("old".to_string(), "old".to_string()), ("new".to_string(), "new".to_string())]);
+        let migrator = NamespaceMigrator::new(clusters.clone(), clusters.clone(), Arc::new(kubeconfigs))
+            .with_poll_interval(Duration::from_millis(1));
+        let options = MigrationOptions {
+            scale_down_source: true,
+            snapshot_classes: HashMap::from([("ebs-gp3".to_string(), "ebs-snapshots".to_string())]),
+            ..Default::default()
+        };
+
+        let failed = migrator.migrate_namespace("old", "new", "shop", options.clone()).await.unwrap();
+        assert_eq!(failed.status, MigrationStatus::Failed);
+        assert_eq!(failed.step, MigrationStep::Apply);
+        assert!(failed.error.unwrap().contains("admission webhook denied Deployment/web"));
+        assert!(migrator.migrate_namespace("old", "new", "shop", options).await.is_err());
+        // Everything ahead of the deployment is in place and will not be redone
+        assert_eq!(clusters.applied.lock().unwrap().first().unwrap(), "CustomResourceDefinition/paymentgateways.shop.sirsi.io");
+        let applied_before = clusters.applied.lock().unwrap().len();
+
+        let done = migrator.resume_migration(&failed.id).await.unwrap();
+        assert_eq!(done.status, MigrationStatus::Completed, "{:?}", done.error);
+        let applied = clusters.applied.lock().unwrap().clone();
+        assert_eq!(applied[applied_before], "Deployment/web");
+        assert_eq!(applied.iter().filter(|a| *a == "Service/web").count(), 1);
+
+        // rsync copies live and again once the source is stopped; the snapshot waits for the stop
+        let transfers = clusters.transfers.lock().unwrap().clone();
+        assert_eq!(
+            transfers,
+            vec![
+                ("scratch".to_string(), TransferMethod::Rsync, false),
+                ("scratch".to_string(), TransferMethod::Rsync, true),
+                ("uploads".to_string(), TransferMethod::Snapshot { snapshot_class: "ebs-snapshots".into() }, true),
+            ]
+        );
+        assert_eq!(clusters.replicas("old", "web"), 0);
+        assert_eq!(clusters.replicas("new", "web"), 3);
+        assert_eq!(done.source_scaled[0].replicas, 3);
+
+        let report = done.report.unwrap();
+        assert!(report.passed, "{:?}", report.problems);
+        assert!(report.object_counts.iter().all(|c| c.source == c.target));
+        assert_eq!(report.object_counts.iter().find(|c| c.kind == "Service").unwrap().target, 2);
+        assert!(migrator.resume_migration(&failed.id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_abandon_restarts_stopped_source_workloads() {
+        let clusters = Arc::new(FakeClusters::default());
+        for object in namespace_objects() {
+            clusters.upsert("old", object);
+        }
+        // The target refuses the ingress, after the source was stopped and data copied
+        *clusters.fail_apply.lock().unwrap() = Some("Ingress/web".into());
+        let kubeconfigs = HashMap::from([("old".to_string(), "old".to_string()), ("new".to_string(), "new".to_string())]);
+        let migrator = NamespaceMigrator::new(clusters.clone(), clusters.clone(), Arc::new(kubeconfigs))
+            .with_poll_interval(Duration::from_millis(1));
+        let options = MigrationOptions { scale_down_source: true, ..Default::default() };
+
+        let failed = migrator.migrate_namespace("old", "new", "shop", options).await.unwrap();
+        assert_eq!(failed.step, MigrationStep::Apply);
+        assert_eq!(clusters.replicas("old", "web"), 3);
+        *clusters.fail_apply.lock().unwrap() = Some("Deployment/web".into());
+        let failed = migrator.resume_migration(&failed.id).await.unwrap();
+        // Cutover failed after the final sync stopped the source
+        assert_eq!(failed.step, MigrationStep::Cutover);
+        assert_eq!(clusters.replicas("old", "web"), 0);
+
+        let abandoned = migrator.abandon_migration(&failed.id).await.unwrap();
+        assert_eq!(abandoned.status, MigrationStatus::Abandoned);
+        assert_eq!(clusters.replicas("old", "web"), 3);
+        assert_eq!(clusters.replicas("new", "web"), 0);
+    }
+
+    // Needs kind and two clusters: KIND_SOURCE and KIND_TARGET name them (default "sirsi-source" and
+    // "sirsi-target"). The rsync transfer exposes the target through a LoadBalancer service, so the
+    // target needs cloud-provider-kind running.
+    #[cfg(feature = "kind-tests")]
+    mod kind {
+        use super::*;
+        use crate::platform::{KubeClusterAccess, KubeVolumeTransfer};
+
+        async fn kubeconfig(cluster: &str) -> String {
+            let output = tokio::process::Command::new("kind")
+                .args(["get", "kubeconfig", "--name", cluster])
+                .output()
+                .await
+                .expect("kind is installed");
+            assert!(output.status.success(), "kind cluster {} is running", cluster);
+            String::from_utf8(output.stdout).unwrap()
+        }
+
+        #[tokio::test]
+        async fn test_migrate_namespace_between_kind_clusters() {
+            let source = std::env::var("KIND_SOURCE").unwrap_or_else(|_| "sirsi-source".to_string());
+            let target = std::env::var("KIND_TARGET").unwrap_or_else(|_| "sirsi-target".to_string());
+            let kubeconfigs = HashMap::from([(source.clone(), kubeconfig(&source).await), (target.clone(), kubeconfig(&target).await)]);
+            let access = Arc::new(KubeClusterAccess::new());
+            let namespace = format!("migrate-test-{}", &uuid::Uuid::new_v4().simple().to_string()[..8]);
+
+            let source_kubeconfig = &kubeconfigs[&source];
+            access.ensure_namespace(source_kubeconfig, &namespace).await.unwrap();
+            let fixtures = [
+                json!({ "apiVersion": "v1", "kind": "ConfigMap", "metadata": { "name": "settings", "namespace": namespace }, "data": { "mode": "live" } }),
+                json!({ "apiVersion": "v1", "kind": "PersistentVolumeClaim", "metadata": { "name": "data", "namespace": namespace },
+                        "spec": { "accessModes": ["ReadWriteOnce"], "resources": { "requests": { "storage": "64Mi" } } } }),
+                json!({ "apiVersion": "apps/v1", "kind": "Deployment", "metadata": { "name": "writer", "namespace": namespace },
+                        "spec": { "replicas": 1, "selector": { "matchLabels": { "app": "writer" } },
+                                  "template": { "metadata": { "labels": { "app": "writer" } },
+                                                "spec": { "containers": [{ "name": "writer", "image": "busybox:1.36",
+                                                                           "command": ["sh", "-c", "date > /data/stamp; sleep 3600"],
+                                                                           "volumeMounts": [{ "name": "data", "mountPath": "/data" }] }],
+                                                          "volumes": [{ "name": "data", "persistentVolumeClaim": { "claimName": "data" } }] } } } }),
+            ];
+            for fixture in &fixtures {
+                access.apply(source_kubeconfig, fixture).await.unwrap();
+            }
+
+            let migrator = NamespaceMigrator::new(access.clone(), Arc::new(KubeVolumeTransfer::new()), Arc::new(kubeconfigs))
+                .with_poll_interval(Duration::from_secs(2));
+            let options = MigrationOptions { scale_down_source: true, wait_timeout: Duration::from_secs(180), ..Default::default() };
+            let record = migrator.migrate_namespace(&source, &target, &namespace, options).await.unwrap();
+            assert_eq!(record.status, MigrationStatus::Completed, "{:?}", record.error);
+            assert!(record.report.unwrap().passed);
+            assert!(record.volumes.iter().all(|v| v.synced && v.passes == 2));
+        }
+    }
+}
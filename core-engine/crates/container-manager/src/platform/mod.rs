@@ -1,3 +1,13 @@
+pub mod cluster;
+pub mod drain;
 mod kubernetes;
+pub mod migration;
+pub mod multicluster;
+pub mod secret_sync;
 
-pub use kubernetes::{KubernetesClient, KubernetesConfig};
+pub use cluster::{ClusterConfig, ClusterManager, ClusterProvisioner, KubeconfigSource, NodePool, Operation};
+pub use drain::{DrainOptions, DrainRecord, MinAvailable, NodeDrainer, WorkloadRef};
+pub use kubernetes::{KubeClusterAccess, KubeVolumeTransfer, KubernetesClient, KubernetesConfig};
+pub use migration::{MigrationOptions, MigrationRecord, MigrationStatus, NamespaceMigrator, TransferMethod, VerificationReport};
+pub use multicluster::{ClusterCredentials, ClusterHealth, ClusterRegistry, FanOut, ScopedCluster, TokenIssuer};
+pub use secret_sync::{SecretSyncer, SyncOutcome, SyncReport, SyncTarget};
@@ -0,0 +1,577 @@
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use sirsi_key_vault::secret::{SecretManager, SecretValue};
+use sirsi_key_vault::KeyVaultError;
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use super::cluster::{CloudProvider, KubeVersion, KubeconfigSource};
+use super::drain::WorkloadInfo;
+use crate::error::{ContainerError, ContainerResult};
+
+// Kubelets may trail the control plane by up to three minor releases, never lead it
+const MAX_KUBELET_SKEW: u32 = 3;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ClusterCredentials {
+    // Key-vault secret holding a kubeconfig with long-lived credentials
+    Kubeconfig { secret_id: String },
+    // Short-lived bearer tokens minted by the provider's issuer from the credentials in the
+    // key-vault secret, e.g. EKS tokens presigned with an IAM key
+    Provider { provider: CloudProvider, endpoint: String, ca_data: String, secret_id: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegisteredCluster {
+    pub name: String,
+    pub credentials: ClusterCredentials,
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+    pub registered_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClusterToken {
+    pub token: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[async_trait]
+pub trait TokenIssuer: Send + Sync {
+    async fn issue(&self, cluster: &str, credentials: &SecretValue) -> ContainerResult<ClusterToken>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NodeSummary {
+    pub name: String,
+    pub ready: bool,
+    pub kubelet_version: String,
+}
+
+// The read calls the registry makes against each cluster. A rejected token surfaces as
+// `ContainerError::Permission`.
+#[async_trait]
+pub trait ClusterApi: Send + Sync {
+    async fn server_version(&self, kubeconfig: &str) -> ContainerResult<String>;
+    async fn nodes(&self, kubeconfig: &str) -> ContainerResult<Vec<NodeSummary>>;
+    // Deployments and StatefulSets in every namespace
+    async fn workloads(&self, kubeconfig: &str, selector: &str) -> ContainerResult<Vec<WorkloadInfo>>;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClusterHealth {
+    pub cluster: String,
+    pub reachable: bool,
+    pub error: Option<String>,
+    pub server_version: Option<String>,
+    pub nodes_ready: usize,
+    pub nodes_total: usize,
+    pub warnings: Vec<String>,
+    pub checked_at: DateTime<Utc>,
+}
+
+impl ClusterHealth {
+    fn unreachable(cluster: &str, error: String, now: DateTime<Utc>) -> Self {
+        Self {
+            cluster: cluster.to_string(),
+            reachable: false,
+            error: Some(error),
+            server_version: None,
+            nodes_ready: 0,
+            nodes_total: 0,
+            warnings: Vec::new(),
+            checked_at: now,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClusterScoped<T> {
+    pub cluster: String,
+    #[serde(flatten)]
+    pub item: T,
+}
+
+// Merged results of a call made on every cluster; one cluster failing only lands it in `failures`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FanOut<T> {
+    pub items: Vec<ClusterScoped<T>>,
+    pub failures: BTreeMap<String, String>,
+}
+
+fn skew_warnings(server_version: &str, nodes: &[NodeSummary]) -> Vec<String> {
+    let Ok(server) = KubeVersion::parse(server_version) else {
+        return vec![format!("Unrecognised server version {}", server_version)];
+    };
+    let mut warnings = Vec::new();
+    for node in nodes {
+        let Ok(kubelet) = KubeVersion::parse(&node.kubelet_version) else { continue };
+        if kubelet > server {
+            warnings.push(format!("Node {} runs kubelet {}, newer than the control plane {}", node.name, kubelet, server));
+        } else if kubelet.major == server.major && server.minor - kubelet.minor > MAX_KUBELET_SKEW {
+            warnings.push(format!(
+                "Node {} runs kubelet {}, more than {} minor releases behind {}",
+                node.name, kubelet, MAX_KUBELET_SKEW, server
+            ));
+        }
+    }
+    warnings
+}
+
+fn token_kubeconfig(cluster: &str, endpoint: &str, ca_data: &str, token: &str) -> String {
+    format!(
+        "apiVersion: v1\nkind: Config\nclusters:\n- name: {cluster}\n  cluster:\n    server: {endpoint}\n    certificate-authority-data: {ca_data}\ncontexts:\n- name: {cluster}\n  context:\n    cluster: {cluster}\n    user: {cluster}\ncurrent-context: {cluster}\nusers:\n- name: {cluster}\n  user:\n    token: {token}\n"
+    )
+}
+
+// The clusters SirsiNexus operates on, with credentials resolved from the key vault.
+// Provider tokens are cached and refreshed ahead of expiry, or when a cluster rejects one.
+pub struct ClusterRegistry {
+    api: Arc<dyn ClusterApi>,
+    secrets: Arc<dyn SecretManager>,
+    issuers: HashMap<CloudProvider, Arc<dyn TokenIssuer>>,
+    clusters: RwLock<BTreeMap<String, RegisteredCluster>>,
+    tokens: RwLock<HashMap<String, ClusterToken>>,
+    health: RwLock<HashMap<String, ClusterHealth>>,
+    refresh_margin: Duration,
+    call_timeout: Duration,
+}
+
+impl ClusterRegistry {
+    pub fn new(api: Arc<dyn ClusterApi>, secrets: Arc<dyn SecretManager>) -> Self {
+        Self {
+            api,
+            secrets,
+            issuers: HashMap::new(),
+            clusters: RwLock::new(BTreeMap::new()),
+            tokens: RwLock::new(HashMap::new()),
+            health: RwLock::new(HashMap::new()),
+            refresh_margin: Duration::from_secs(120),
+            call_timeout: Duration::from_secs(20),
+        }
+    }
+
+    pub fn with_token_issuer(mut self, provider: CloudProvider, issuer: Arc<dyn TokenIssuer>) -> Self {
+        self.issuers.insert(provider, issuer);
+        self
+    }
+
+    // How long before expiry a cached token is replaced
+    pub fn with_refresh_margin(mut self, margin: Duration) -> Self {
+        self.refresh_margin = margin;
+        self
+    }
+
+    // Per-cluster limit on each call, so one slow API server can't hold up a fan-out
+    pub fn with_call_timeout(mut self, timeout: Duration) -> Self {
+        self.call_timeout = timeout;
+        self
+    }
+
+    // Resolves the credentials once so a bad secret or missing issuer fails here
+    pub async fn register(
+        &self,
+        name: &str,
+        credentials: ClusterCredentials,
+        labels: HashMap<String, String>,
+    ) -> ContainerResult<RegisteredCluster> {
+        if name.is_empty() {
+            return Err(ContainerError::Validation("Clusters need a name".into()));
+        }
+        if self.clusters.read().await.contains_key(name) {
+            return Err(ContainerError::Validation(format!("Cluster {} is already registered", name)));
+        }
+        let cluster = RegisteredCluster { name: name.to_string(), credentials, labels, registered_at: Utc::now() };
+        self.resolve(&cluster, false).await?;
+        info!(cluster = name, "Registered cluster");
+        self.clusters.write().await.insert(name.to_string(), cluster.clone());
+        Ok(cluster)
+    }
+
+    pub async fn deregister(&self, name: &str) -> ContainerResult<()> {
+        self.clusters
+            .write()
+            .await
+            .remove(name)
+            .ok_or_else(|| ContainerError::NotFound(format!("Cluster {} is not registered", name)))?;
+        self.tokens.write().await.remove(name);
+        self.health.write().await.remove(name);
+        Ok(())
+    }
+
+    pub async fn clusters(&self) -> Vec<RegisteredCluster> {
+        self.clusters.read().await.values().cloned().collect()
+    }
+
+    async fn cluster(&self, name: &str) -> ContainerResult<RegisteredCluster> {
+        self.clusters
+            .read()
+            .await
+            .get(name)
+            .cloned()
+            .ok_or_else(|| ContainerError::NotFound(format!("Cluster {} is not registered", name)))
+    }
+
+    pub async fn for_cluster(&self, name: &str) -> ContainerResult<ScopedCluster<'_>> {
+        let cluster = self.cluster(name).await?;
+        Ok(ScopedCluster { registry: self, cluster })
+    }
+
+    async fn secret(&self, secret_id: &str) -> ContainerResult<SecretValue> {
+        let secret = self.secrets.get_secret(secret_id).await.map_err(|e| match e {
+            KeyVaultError::NotFound(_) => ContainerError::NotFound(format!("Cluster credential secret {} not found", secret_id)),
+            KeyVaultError::Permission(msg) => ContainerError::Permission(msg),
+            e => ContainerError::Internal(format!("Failed to read cluster credential secret {}: {}", secret_id, e)),
+        })?;
+        Ok(secret.value)
+    }
+
+    async fn resolve(&self, cluster: &RegisteredCluster, refresh: bool) -> ContainerResult<String> {
+        match &cluster.credentials {
+            ClusterCredentials::Kubeconfig { secret_id } => match self.secret(secret_id).await? {
+                SecretValue::Plain(kubeconfig) => Ok(kubeconfig),
+                _ => Err(ContainerError::Config(format!("Secret {} is not a kubeconfig", secret_id))),
+            },
+            ClusterCredentials::Provider { provider, endpoint, ca_data, secret_id } => {
+                let fresh_until = Utc::now() + chrono::Duration::from_std(self.refresh_margin).unwrap_or(chrono::Duration::MAX);
+                let cached = self.tokens.read().await.get(&cluster.name).cloned();
+                if let Some(token) = cached.filter(|t| !refresh && t.expires_at > fresh_until) {
+                    return Ok(token_kubeconfig(&cluster.name, endpoint, ca_data, &token.token));
+                }
+                let issuer = self.issuers.get(provider).ok_or_else(|| {
+                    ContainerError::Config(format!("No token issuer configured for {} clusters", provider.as_str()))
+                })?;
+                let token = issuer.issue(&cluster.name, &self.secret(secret_id).await?).await?;
+                info!(cluster = %cluster.name, expires_at = %token.expires_at, "Refreshed cluster token");
+                let kubeconfig = token_kubeconfig(&cluster.name, endpoint, ca_data, &token.token);
+                self.tokens.write().await.insert(cluster.name.clone(), token);
+                Ok(kubeconfig)
+            }
+        }
+    }
+
+    // Runs one call against a cluster under the timeout, retrying once with a new token if
+    // the cluster rejected the cached one
+    async fn call<T, F, Fut>(&self, cluster: &RegisteredCluster, call: F) -> ContainerResult<T>
+    where
+        F: Fn(Arc<dyn ClusterApi>, String) -> Fut,
+        Fut: Future<Output = ContainerResult<T>>,
+    {
+        let attempt = |refresh: bool| {
+            let call = &call;
+            async move {
+                let kubeconfig = self.resolve(cluster, refresh).await?;
+                tokio::time::timeout(self.call_timeout, call(self.api.clone(), kubeconfig))
+                    .await
+                    .map_err(|_| ContainerError::Network(format!("Cluster {} did not answer within {:?}", cluster.name, self.call_timeout)))?
+            }
+        };
+        match attempt(false).await {
+            Err(ContainerError::Permission(msg)) if matches!(cluster.credentials, ClusterCredentials::Provider { .. }) => {
+                warn!(cluster = %cluster.name, "Cluster rejected its token, refreshing: {}", msg);
+                attempt(true).await
+            }
+            result => result,
+        }
+    }
+
+    async fn fan_out<T, F, Fut>(&self, call: F) -> FanOut<T>
+    where
+        F: Fn(Arc<dyn ClusterApi>, String) -> Fut,
+        Fut: Future<Output = ContainerResult<Vec<T>>>,
+    {
+        let clusters = self.clusters().await;
+        let results = join_all(clusters.iter().map(|cluster| self.call(cluster, &call))).await;
+        let mut merged = FanOut { items: Vec::new(), failures: BTreeMap::new() };
+        for (cluster, result) in clusters.into_iter().zip(results) {
+            match result {
+                Ok(items) => merged
+                    .items
+                    .extend(items.into_iter().map(|item| ClusterScoped { cluster: cluster.name.clone(), item })),
+                Err(e) => {
+                    warn!(cluster = %cluster.name, "Cluster call failed: {}", e);
+                    merged.failures.insert(cluster.name, e.to_string());
+                }
+            }
+        }
+        merged
+    }
+
+    pub async fn list_workloads_everywhere(&self, selector: &str) -> FanOut<WorkloadInfo> {
+        self.fan_out(|api, kubeconfig| async move { api.workloads(&kubeconfig, selector).await }).await
+    }
+
+    async fn check(&self, cluster: &RegisteredCluster) -> ClusterHealth {
+        let now = Utc::now();
+        let version = match self.call(cluster, |api, kubeconfig| async move { api.server_version(&kubeconfig).await }).await {
+            Ok(version) => version,
+            Err(e) => return ClusterHealth::unreachable(&cluster.name, e.to_string(), now),
+        };
+        let mut health = ClusterHealth {
+            cluster: cluster.name.clone(),
+            reachable: true,
+            error: None,
+            server_version: Some(version.clone()),
+            nodes_ready: 0,
+            nodes_total: 0,
+            warnings: Vec::new(),
+            checked_at: now,
+        };
+        match self.call(cluster, |api, kubeconfig| async move { api.nodes(&kubeconfig).await }).await {
+            Ok(nodes) => {
+                health.nodes_total = nodes.len();
+                health.nodes_ready = nodes.iter().filter(|n| n.ready).count();
+                health.warnings = skew_warnings(&version, &nodes);
+            }
+            Err(e) => health.error = Some(format!("Failed to list nodes: {}", e)),
+        }
+        health
+    }
+
+    pub async fn poll_health(&self) -> Vec<ClusterHealth> {
+        let clusters = self.clusters().await;
+        let checks = join_all(clusters.iter().map(|cluster| self.check(cluster))).await;
+        let mut health = self.health.write().await;
+        for check in &checks {
+            health.insert(check.cluster.clone(), check.clone());
+        }
+        checks
+    }
+
+    // As of the last poll
+    pub async fn health(&self, name: &str) -> Option<ClusterHealth> {
+        self.health.read().await.get(name).cloned()
+    }
+
+    pub fn spawn_health_poller(self: Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                for health in self.poll_health().await.iter().filter(|h| !h.reachable) {
+                    warn!(cluster = %health.cluster, "Cluster unreachable: {}", health.error.as_deref().unwrap_or_default());
+                }
+            }
+        })
+    }
+}
+
+#[async_trait]
+impl KubeconfigSource for ClusterRegistry {
+    async fn kubeconfig(&self, cluster: &str) -> ContainerResult<String> {
+        let cluster = self.cluster(cluster).await?;
+        self.resolve(&cluster, false).await
+    }
+}
+
+// One registered cluster's calls, with its credentials kept current
+pub struct ScopedCluster<'a> {
+    registry: &'a ClusterRegistry,
+    cluster: RegisteredCluster,
+}
+
+impl ScopedCluster<'_> {
+    pub fn name(&self) -> &str {
+        &self.cluster.name
+    }
+
+    pub async fn kubeconfig(&self) -> ContainerResult<String> {
+        self.registry.resolve(&self.cluster, false).await
+    }
+
+    pub async fn server_version(&self) -> ContainerResult<String> {
+        self.registry.call(&self.cluster, |api, kubeconfig| async move { api.server_version(&kubeconfig).await }).await
+    }
+
+    pub async fn nodes(&self) -> ContainerResult<Vec<NodeSummary>> {
+        self.registry.call(&self.cluster, |api, kubeconfig| async move { api.nodes(&kubeconfig).await }).await
+    }
+
+    pub async fn workloads(&self, selector: &str) -> ContainerResult<Vec<WorkloadInfo>> {
+        self.registry.call(&self.cluster, |api, kubeconfig| async move { api.workloads(&kubeconfig, selector).await }).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::sync::Mutex;
+
+    use sirsi_key_vault::secret::{InMemorySecretManager, Secret};
+
+    use super::*;
+    use crate::platform::drain::{WorkloadKind, WorkloadRef};
+
+    fn field<'a>(kubeconfig: &'a str, key: &str) -> Option<&'a str> {
+        kubeconfig.lines().find_map(|line| line.trim().strip_prefix(key))
+    }
+
+    // Two API servers told apart by the kubeconfig's server; west is down and slow never answers
+    #[derive(Default)]
+    struct FakeServers {
+        revoked: Mutex<HashSet<String>>,
+        tokens_seen: Mutex<Vec<String>>,
+    }
+
+    impl FakeServers {
+        async fn serve(&self, kubeconfig: &str) -> ContainerResult<&'static str> {
+            if let Some(token) = field(kubeconfig, "token: ") {
+                self.tokens_seen.lock().unwrap().push(token.to_string());
+                if self.revoked.lock().unwrap().contains(token) {
+                    return Err(ContainerError::Permission("Unauthorized".into()));
+                }
+            }
+            match field(kubeconfig, "server: ") {
+                Some("https://east.example") => Ok("east"),
+                Some("https://eks.example") => Ok("eks"),
+                Some("https://slow.example") => {
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    Ok("slow")
+                }
+                other => Err(ContainerError::Network(format!("connection refused by {:?}", other))),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl ClusterApi for FakeServers {
+        async fn server_version(&self, kubeconfig: &str) -> ContainerResult<String> {
+            self.serve(kubeconfig).await.map(|_| "v1.29.4".to_string())
+        }
+
+        async fn nodes(&self, kubeconfig: &str) -> ContainerResult<Vec<NodeSummary>> {
+            self.serve(kubeconfig).await?;
+            let node = |name: &str, ready: bool, version: &str| NodeSummary { name: name.into(), ready, kubelet_version: version.into() };
+            Ok(vec![node("a", true, "v1.29.4"), node("b", true, "v1.25.9"), node("c", false, "v1.29.4")])
+        }
+
+        async fn workloads(&self, kubeconfig: &str, _selector: &str) -> ContainerResult<Vec<WorkloadInfo>> {
+            let server = self.serve(kubeconfig).await?;
+            Ok(vec![WorkloadInfo {
+                workload: WorkloadRef { namespace: "shop".into(), kind: WorkloadKind::Deployment, name: format!("web-{}", server) },
+                replicas: 2,
+                selector: BTreeMap::new(),
+            }])
+        }
+    }
+
+    struct Issuer {
+        issued: Mutex<u32>,
+        lifetime: Mutex<chrono::Duration>,
+    }
+
+    #[async_trait]
+    impl TokenIssuer for Issuer {
+        async fn issue(&self, _cluster: &str, credentials: &SecretValue) -> ContainerResult<ClusterToken> {
+            assert!(matches!(credentials, SecretValue::Plain(key) if key == "iam-key"));
+            let mut issued = self.issued.lock().unwrap();
+            *issued += 1;
+            Ok(ClusterToken { token: format!("k8s-aws-v1.{}", issued), expires_at: Utc::now() + *self.lifetime.lock().unwrap() })
+        }
+    }
+
+    async fn vault(secrets: &[(&str, &str)]) -> Arc<InMemorySecretManager> {
+        let vault = Arc::new(InMemorySecretManager::new());
+        for (id, value) in secrets {
+            let now = Utc::now();
+            let secret = Secret {
+                id: id.to_string(),
+                name: id.to_string(),
+                description: None,
+                value: SecretValue::Plain(value.to_string()),
+                version: 0,
+                created_at: now,
+                updated_at: now,
+                expires_at: None,
+                metadata: HashMap::new(),
+                labels: HashMap::new(),
+                rotation_policy: None,
+            };
+            vault.create_secret(secret).await.unwrap();
+        }
+        vault
+    }
+
+    fn kubeconfig_secret(id: &str) -> ClusterCredentials {
+        ClusterCredentials::Kubeconfig { secret_id: id.into() }
+    }
+
+    #[tokio::test]
+    async fn test_fan_out_isolates_failing_clusters() {
+        let vault = vault(&[
+            ("kubeconfig/east", "clusters:\n- cluster:\n    server: https://east.example\n"),
+            ("kubeconfig/west", "clusters:\n- cluster:\n    server: https://west.example\n"),
+            ("kubeconfig/slow", "clusters:\n- cluster:\n    server: https://slow.example\n"),
+        ])
+        .await;
+        let registry = ClusterRegistry::new(Arc::new(FakeServers::default()), vault).with_call_timeout(Duration::from_millis(50));
+        for name in ["east", "west", "slow"] {
+            registry.register(name, kubeconfig_secret(&format!("kubeconfig/{}", name)), HashMap::new()).await.unwrap();
+        }
+        assert!(registry.register("north", kubeconfig_secret("kubeconfig/north"), HashMap::new()).await.is_err());
+
+        let workloads = registry.list_workloads_everywhere("app=web").await;
+        assert_eq!(workloads.items.len(), 1);
+        assert_eq!(workloads.items[0].cluster, "east");
+        assert_eq!(workloads.items[0].item.workload.name, "web-east");
+        assert_eq!(workloads.failures.keys().collect::<Vec<_>>(), vec!["slow", "west"]);
+        assert!(workloads.failures["west"].contains("connection refused"));
+        assert!(workloads.failures["slow"].contains("did not answer"));
+
+        let health = registry.poll_health().await;
+        assert_eq!(health.len(), 3);
+        let east = registry.health("east").await.unwrap();
+        assert!(east.reachable);
+        assert_eq!((east.nodes_ready, east.nodes_total), (2, 3));
+        assert_eq!(east.warnings.len(), 1);
+        assert!(east.warnings[0].contains("Node b runs kubelet 1.25"));
+        assert!(!registry.health("west").await.unwrap().reachable);
+
+        let scoped = registry.for_cluster("east").await.unwrap();
+        assert_eq!(scoped.server_version().await.unwrap(), "v1.29.4");
+        assert!(registry.for_cluster("north").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_provider_tokens_refresh_before_expiry_and_on_rejection() {
+        let servers = Arc::new(FakeServers::default());
+        let issuer = Arc::new(Issuer { issued: Mutex::new(0), lifetime: Mutex::new(chrono::Duration::minutes(15)) });
+        let registry = ClusterRegistry::new(servers.clone(), vault(&[("aws/prod", "iam-key")]).await)
+            .with_token_issuer(CloudProvider::Eks, issuer.clone())
+            .with_refresh_margin(Duration::from_secs(60));
+        let credentials = ClusterCredentials::Provider {
+            provider: CloudProvider::Eks,
+            endpoint: "https://eks.example".into(),
+            ca_data: "LS0tLS1CRUdJTg==".into(),
+            secret_id: "aws/prod".into(),
+        };
+        registry.register("eks-prod", credentials, HashMap::new()).await.unwrap();
+        let scoped = registry.for_cluster("eks-prod").await.unwrap();
+        scoped.nodes().await.unwrap();
+        scoped.workloads("app=web").await.unwrap();
+        assert_eq!(*issuer.issued.lock().unwrap(), 1);
+
+        // A token the cluster stopped accepting is replaced and the call retried
+        servers.revoked.lock().unwrap().insert("k8s-aws-v1.1".into());
+        assert_eq!(scoped.server_version().await.unwrap(), "v1.29.4");
+        assert_eq!(*issuer.issued.lock().unwrap(), 2);
+        assert_eq!(servers.tokens_seen.lock().unwrap().last().unwrap(), "k8s-aws-v1.2");
+
+        // Tokens inside the refresh margin are replaced before they are used
+        *issuer.lifetime.lock().unwrap() = chrono::Duration::seconds(30);
+        servers.revoked.lock().unwrap().insert("k8s-aws-v1.2".into());
+        scoped.server_version().await.unwrap();
+        scoped.server_version().await.unwrap();
+        assert_eq!(*issuer.issued.lock().unwrap(), 4);
+        assert!(registry.kubeconfig("eks-prod").await.unwrap().contains("token: k8s-aws-v1.5"));
+    }
+}
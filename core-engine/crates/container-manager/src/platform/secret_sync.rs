@@ -0,0 +1,562 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sirsi_key_vault::secret::{AccessPolicyManager, RotationEvent, Secret, SecretAction, SecretFilter, SecretManager, SecretValue};
+use sirsi_key_vault::KeyVaultError;
+use tokio::sync::{mpsc, RwLock};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use super::cluster::KubeconfigSource;
+use crate::error::{ContainerError, ContainerResult};
+use crate::service::{MANAGED_BY_LABEL, MANAGED_BY_VALUE};
+
+pub const SOURCE_ID_ANNOTATION: &str = "sirsi.io/source-id";
+pub const SOURCE_VERSION_ANNOTATION: &str = "sirsi.io/source-version";
+// Ties a Secret to the sync target that owns it, so pruning never touches anything else
+pub const SYNC_TARGET_LABEL: &str = "sirsi.io/sync-target";
+
+// A Kubernetes Secret as the syncer reads and writes it; `data` holds raw, not base64, bytes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KubeSecret {
+    pub namespace: String,
+    pub name: String,
+    pub type_: String,
+    pub data: BTreeMap<String, Vec<u8>>,
+    pub labels: BTreeMap<String, String>,
+    pub annotations: BTreeMap<String, String>,
+}
+
+#[async_trait]
+pub trait SecretSyncApi: Send + Sync {
+    async fn get_secret(&self, kubeconfig: &str, namespace: &str, name: &str) -> ContainerResult<Option<KubeSecret>>;
+    // Replaces the whole object, so keys added by hand do not survive a sync
+    async fn put_secret(&self, kubeconfig: &str, secret: &KubeSecret) -> ContainerResult<()>;
+    async fn delete_secret(&self, kubeconfig: &str, namespace: &str, name: &str) -> ContainerResult<()>;
+    async fn list_secrets(&self, kubeconfig: &str, namespace: &str, labels: &str) -> ContainerResult<Vec<KubeSecret>>;
+    async fn record_event(&self, kubeconfig: &str, namespace: &str, secret: &str, reason: &str, message: &str) -> ContainerResult<()>;
+}
+
+// Which key-vault secrets land in a namespace. Kubernetes names default to the secret's name
+// made DNS safe; `with_name` overrides one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncTarget {
+    pub id: String,
+    pub cluster: String,
+    pub namespace: String,
+    pub filter: SecretFilter,
+    pub names: HashMap<String, String>,
+    // Delete synced Secrets whose source is gone or no longer selected
+    pub prune: bool,
+}
+
+impl SyncTarget {
+    pub fn new(id: impl Into<String>, cluster: impl Into<String>, namespace: impl Into<String>, filter: SecretFilter) -> Self {
+        Self {
+            id: id.into(),
+            cluster: cluster.into(),
+            namespace: namespace.into(),
+            filter,
+            names: HashMap::new(),
+            prune: false,
+        }
+    }
+
+    pub fn with_name(mut self, secret_id: impl Into<String>, name: impl Into<String>) -> Self {
+        self.names.insert(secret_id.into(), name.into());
+        self
+    }
+
+    pub fn with_prune(mut self, prune: bool) -> Self {
+        self.prune = prune;
+        self
+    }
+
+    // The principal access policies must grant `Read` for a secret to reach this namespace
+    pub fn principal(&self) -> String {
+        namespace_principal(&self.cluster, &self.namespace)
+    }
+
+    fn kube_name(&self, secret: &Secret) -> String {
+        self.names.get(&secret.id).cloned().unwrap_or_else(|| dns_name(&secret.name))
+    }
+}
+
+pub fn namespace_principal(cluster: &str, namespace: &str) -> String {
+    format!("k8s:{}/{}", cluster, namespace)
+}
+
+fn dns_name(name: &str) -> String {
+    let mut out = String::new();
+    for c in name.chars() {
+        let c = c.to_ascii_lowercase();
+        if c.is_ascii_alphanumeric() || c == '.' {
+            out.push(c);
+        } else if !out.ends_with('-') {
+            out.push('-');
+        }
+    }
+    let out = out.trim_matches(|c| c == '-' || c == '.');
+    out[..out.len().min(253)].to_string()
+}
+
+// The Secret type and keys a key-vault value maps to. Plain values holding a JSON object of
+// strings become one key per field, the same fields `vault:<id>#<field>` reads.
+pub fn secret_data(value: &SecretValue) -> (String, BTreeMap<String, Vec<u8>>) {
+    let mut data = BTreeMap::new();
+    let type_ = match value {
+        SecretValue::Plain(plain) => {
+            match serde_json::from_str::<BTreeMap<String, String>>(plain) {
+                Ok(fields) => data.extend(fields.into_iter().map(|(k, v)| (k, v.into_bytes()))),
+                Err(_) => {
+                    data.insert("value".to_string(), plain.clone().into_bytes());
+                }
+            }
+            "Opaque"
+        }
+        SecretValue::Encrypted(bytes) => {
+            data.insert("value".to_string(), bytes.clone());
+            "Opaque"
+        }
+        SecretValue::Certificate(cert) => {
+            // Ingress controllers expect the chain after the leaf in tls.crt
+            let mut pem = cert.certificate.trim_end().to_string();
+            for intermediate in cert.chain.iter().flatten() {
+                pem.push('\n');
+                pem.push_str(intermediate.trim_end());
+            }
+            pem.push('\n');
+            data.insert("tls.crt".to_string(), pem.into_bytes());
+            data.insert("tls.key".to_string(), cert.private_key.clone().into_bytes());
+            "kubernetes.io/tls"
+        }
+        SecretValue::SSH(ssh) => {
+            data.insert("ssh-privatekey".to_string(), ssh.private_key.clone().into_bytes());
+            data.insert("ssh-publickey".to_string(), ssh.public_key.clone().into_bytes());
+            if let Some(passphrase) = &ssh.passphrase {
+                data.insert("passphrase".to_string(), passphrase.clone().into_bytes());
+            }
+            "kubernetes.io/ssh-auth"
+        }
+    };
+    (type_.to_string(), data)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "outcome", content = "reason", rename_all = "snake_case")]
+pub enum SyncOutcome {
+    Created,
+    Updated,
+    Unchanged,
+    // The Secret was edited in the cluster and has been overwritten
+    DriftCorrected,
+    Pruned,
+    Denied(String),
+    Failed(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SecretSyncResult {
+    pub secret_id: Option<String>,
+    pub name: String,
+    pub version: Option<i32>,
+    pub outcome: SyncOutcome,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncReport {
+    pub target: String,
+    pub results: Vec<SecretSyncResult>,
+}
+
+impl SyncReport {
+    pub fn outcome(&self, name: &str) -> Option<&SyncOutcome> {
+        self.results.iter().find(|r| r.name == name).map(|r| &r.outcome)
+    }
+}
+
+// Keeps Kubernetes Secrets in step with key-vault: on a schedule, on rotation, and against
+// edits made in the cluster
+pub struct SecretSyncer {
+    api: Arc<dyn SecretSyncApi>,
+    kubeconfigs: Arc<dyn KubeconfigSource>,
+    secrets: Arc<dyn SecretManager>,
+    policies: Arc<dyn AccessPolicyManager>,
+    targets: RwLock<BTreeMap<String, SyncTarget>>,
+}
+
+impl SecretSyncer {
+    pub fn new(
+        api: Arc<dyn SecretSyncApi>,
+        kubeconfigs: Arc<dyn KubeconfigSource>,
+        secrets: Arc<dyn SecretManager>,
+        policies: Arc<dyn AccessPolicyManager>,
+    ) -> Self {
+        Self { api, kubeconfigs, secrets, policies, targets: RwLock::new(BTreeMap::new()) }
+    }
+
+    pub async fn add_target(&self, target: SyncTarget) -> ContainerResult<()> {
+        if target.id.is_empty() || dns_name(&target.id) != target.id || target.id.len() > 63 {
+            return Err(ContainerError::Validation(format!("Sync target id '{}' must be a valid label value", target.id)));
+        }
+        for name in target.names.values() {
+            if dns_name(name) != *name {
+                return Err(ContainerError::Validation(format!("'{}' is not a valid Secret name", name)));
+            }
+        }
+        self.targets.write().await.insert(target.id.clone(), target);
+        Ok(())
+    }
+
+    pub async fn remove_target(&self, id: &str) -> ContainerResult<SyncTarget> {
+        self.targets
+            .write()
+            .await
+            .remove(id)
+            .ok_or_else(|| ContainerError::NotFound(format!("Sync target {} not found", id)))
+    }
+
+    pub async fn targets(&self) -> Vec<SyncTarget> {
+        self.targets.read().await.values().cloned().collect()
+    }
+
+    pub async fn sync_all(&self) -> Vec<ContainerResult<SyncReport>> {
+        let mut reports = Vec::new();
+        for target in self.targets().await {
+            reports.push(self.sync_target(&target).await);
+        }
+        reports
+    }
+
+    // Re-syncs every target that selects the rotated secret. A deleted secret matches no
+    // filter, so pruning is left to the next scheduled sync.
+    pub async fn on_rotation(&self, event: &RotationEvent) -> Vec<ContainerResult<SyncReport>> {
+        let secret = match self.secrets.get_secret(&event.secret_id).await {
+            Ok(secret) => secret,
+            Err(e) => return vec![Err(vault_error(&event.secret_id, e))],
+        };
+        let mut reports = Vec::new();
+        for target in self.targets().await.into_iter().filter(|t| t.filter.matches(&secret)) {
+            info!(target = %target.id, secret = %event.secret_id, version = event.new_version, "Propagating rotated secret");
+            reports.push(self.sync_target(&target).await);
+        }
+        reports
+    }
+
+    pub async fn sync_target(&self, target: &SyncTarget) -> ContainerResult<SyncReport> {
+        let kubeconfig = self.kubeconfigs.kubeconfig(&target.cluster).await?;
+        let sources: Vec<Secret> = self
+            .secrets
+            .list_secrets()
+            .await
+            .map_err(|e| ContainerError::Platform(format!("Failed to list key-vault secrets: {}", e)))?
+            .into_iter()
+            .filter(|s| target.filter.matches(s))
+            .collect();
+
+        let principal = target.principal();
+        let mut results = Vec::new();
+        let mut synced = BTreeSet::new();
+        for source in &sources {
+            let name = target.kube_name(source);
+            let outcome = match self.policies.validate_access(&principal, &source.id, SecretAction::Read).await {
+                Ok(true) => {
+                    synced.insert(name.clone());
+                    self.sync_one(&kubeconfig, target, &name, source)
+                        .await
+                        .unwrap_or_else(|e| SyncOutcome::Failed(e.to_string()))
+                }
+                Ok(false) => SyncOutcome::Denied(format!("{} may not read {}", principal, source.id)),
+                Err(e) => SyncOutcome::Failed(format!("Access check failed: {}", e)),
+            };
+            results.push(SecretSyncResult {
+                secret_id: Some(source.id.clone()),
+                name,
+                version: Some(source.version),
+                outcome,
+            });
+        }
+
+        if target.prune {
+            let selector = format!("{}={}", SYNC_TARGET_LABEL, target.id);
+            for stale in self.api.list_secrets(&kubeconfig, &target.namespace, &selector).await? {
+                if synced.contains(&stale.name) {
+                    continue;
+                }
+                let outcome = match self.api.delete_secret(&kubeconfig, &target.namespace, &stale.name).await {
+                    Ok(()) => {
+                        info!(namespace = %target.namespace, secret = %stale.name, "Pruned synced secret");
+                        SyncOutcome::Pruned
+                    }
+                    Err(e) => SyncOutcome::Failed(e.to_string()),
+                };
+                results.push(SecretSyncResult {
+                    secret_id: stale.annotations.get(SOURCE_ID_ANNOTATION).cloned(),
+                    name: stale.name,
+                    version: None,
+                    outcome,
+                });
+            }
+        }
+        Ok(SyncReport { target: target.id.clone(), results })
+    }
+
+    async fn sync_one(&self, kubeconfig: &str, target: &SyncTarget, name: &str, source: &Secret) -> ContainerResult<SyncOutcome> {
+        let (type_, data) = secret_data(&source.value);
+        let desired = KubeSecret {
+            namespace: target.namespace.clone(),
+            name: name.to_string(),
+            type_,
+            data,
+            labels: BTreeMap::from([
+                (MANAGED_BY_LABEL.to_string(), MANAGED_BY_VALUE.to_string()),
+                (SYNC_TARGET_LABEL.to_string(), target.id.clone()),
+            ]),
+            annotations: BTreeMap::from([
+                (SOURCE_ID_ANNOTATION.to_string(), source.id.clone()),
+                (SOURCE_VERSION_ANNOTATION.to_string(), source.version.to_string()),
+            ]),
+        };
+        let outcome = match self.api.get_secret(kubeconfig, &target.namespace, name).await? {
+            None => SyncOutcome::Created,
+            Some(current) => {
+                let owner = current.labels.get(SYNC_TARGET_LABEL);
+                if owner != Some(&target.id) {
+                    return Err(ContainerError::Validation(format!(
+                        "Secret {}/{} exists and is not managed by sync target {}",
+                        target.namespace, name, target.id
+                    )));
+                }
+                let same_version = current.annotations.get(SOURCE_VERSION_ANNOTATION) == Some(&source.version.to_string())
+                    && current.annotations.get(SOURCE_ID_ANNOTATION) == Some(&source.id);
+                let same_content = current.type_ == desired.type_ && current.data == desired.data;
+                match (same_version, same_content) {
+                    (true, true) => return Ok(SyncOutcome::Unchanged),
+                    (false, _) => SyncOutcome::Updated,
+                    (true, false) => SyncOutcome::DriftCorrected,
+                }
+            }
+        };
+        self.api.put_secret(kubeconfig, &desired).await?;
+        if outcome == SyncOutcome::DriftCorrected {
+            let message = format!("Overwrote manual changes with {} version {}", source.id, source.version);
+            warn!(namespace = %target.namespace, secret = name, "{}", message);
+            // The correction has landed either way; the event is only for whoever made the edit
+            if let Err(e) = self.api.record_event(kubeconfig, &target.namespace, name, "SecretDriftCorrected", &message).await {
+                warn!(namespace = %target.namespace, secret = name, "Failed to record drift event: {}", e);
+            }
+        }
+        Ok(outcome)
+    }
+
+    // Syncs everything on each tick, and the affected targets as rotations arrive
+    pub fn spawn_syncer(self: Arc<Self>, interval: Duration, mut rotations: mpsc::Receiver<RotationEvent>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            let mut rotations_open = true;
+            loop {
+                let reports = tokio::select! {
+                    _ = ticker.tick() => self.sync_all().await,
+                    event = rotations.recv(), if rotations_open => match event {
+                        Some(event) => self.on_rotation(&event).await,
+                        None => {
+                            rotations_open = false;
+                            continue;
+                        }
+                    },
+                };
+                for report in reports {
+                    match report {
+                        Ok(report) => {
+                            for failed in report.results.iter().filter(|r| matches!(r.outcome, SyncOutcome::Failed(_))) {
+                                warn!(target = %report.target, secret = %failed.name, "Secret sync failed: {:?}", failed.outcome);
+                            }
+                        }
+                        Err(e) => warn!("Secret sync failed: {}", e),
+                    }
+                }
+            }
+        })
+    }
+}
+
+fn vault_error(id: &str, e: KeyVaultError) -> ContainerError {
+    match e {
+        KeyVaultError::NotFound(msg) => ContainerError::NotFound(msg),
+        e => ContainerError::Platform(format!("Failed to read key-vault secret {}: {}", id, e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use chrono::Utc;
+    use sirsi_key_vault::secret::{
+        AccessPolicy, CertificateSecret, InMemoryAccessPolicyManager, InMemorySecretManager, RotationReason, SecretPermission,
+    };
+
+    use super::*;
+
+    #[derive(Default)]
+    struct FakeCluster {
+        secrets: Mutex<BTreeMap<(String, String), KubeSecret>>,
+        events: Mutex<Vec<(String, String)>>,
+    }
+
+    #[async_trait]
+    impl SecretSyncApi for FakeCluster {
+        async fn get_secret(&self, _kubeconfig: &str, namespace: &str, name: &str) -> ContainerResult<Option<KubeSecret>> {
+            Ok(self.secrets.lock().unwrap().get(&(namespace.to_string(), name.to_string())).cloned())
+        }
+
+        async fn put_secret(&self, _kubeconfig: &str, secret: &KubeSecret) -> ContainerResult<()> {
+            self.secrets.lock().unwrap().insert((secret.namespace.clone(), secret.name.clone()), secret.clone());
+            Ok(())
+        }
+
+        async fn delete_secret(&self, _kubeconfig: &str, namespace: &str, name: &str) -> ContainerResult<()> {
+            self.secrets.lock().unwrap().remove(&(namespace.to_string(), name.to_string()));
+            Ok(())
+        }
+
+        async fn list_secrets(&self, _kubeconfig: &str, namespace: &str, labels: &str) -> ContainerResult<Vec<KubeSecret>> {
+            let (key, value) = labels.split_once('=').unwrap();
+            Ok(self
+                .secrets
+                .lock()
+                .unwrap()
+                .values()
+                .filter(|s| s.namespace == namespace && s.labels.get(key).map(String::as_str) == Some(value))
+                .cloned()
+                .collect())
+        }
+
+        async fn record_event(&self, _kubeconfig: &str, _namespace: &str, secret: &str, reason: &str, _message: &str) -> ContainerResult<()> {
+            self.events.lock().unwrap().push((secret.to_string(), reason.to_string()));
+            Ok(())
+        }
+    }
+
+    fn secret(id: &str, value: SecretValue) -> Secret {
+        Secret {
+            id: id.to_string(),
+            name: id.to_string(),
+            description: None,
+            value,
+            version: 0,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            expires_at: None,
+            metadata: HashMap::new(),
+            labels: HashMap::from([("sync".to_string(), "shop".to_string())]),
+            rotation_policy: None,
+        }
+    }
+
+    async fn setup() -> (Arc<FakeCluster>, Arc<InMemorySecretManager>, SecretSyncer) {
+        let cluster = Arc::new(FakeCluster::default());
+        let vault = Arc::new(InMemorySecretManager::new());
+        vault.create_secret(secret("shop/db", SecretValue::Plain(r#"{"username":"shop","password":"hunter2"}"#.into()))).await.unwrap();
+        let cert = CertificateSecret {
+            certificate: "-----LEAF-----".into(),
+            private_key: "-----KEY-----".into(),
+            chain: Some(vec!["-----INTERMEDIATE-----".into()]),
+        };
+        vault.create_secret(secret("shop/tls", SecretValue::Certificate(cert))).await.unwrap();
+        vault.create_secret(secret("shop/keystore", SecretValue::Encrypted(vec![0, 159, 255]))).await.unwrap();
+        vault.create_secret(secret("billing/api", SecretValue::Plain("not for shop".into()))).await.unwrap();
+
+        let policies = Arc::new(InMemoryAccessPolicyManager::new());
+        policies
+            .create_policy(AccessPolicy {
+                id: "shop-sync".into(),
+                name: "shop namespace".into(),
+                description: None,
+                principals: vec![namespace_principal("prod", "shop")],
+                permissions: vec![SecretPermission { actions: vec![SecretAction::Read], secret_patterns: vec!["shop/*".into()] }],
+                conditions: None,
+            })
+            .await
+            .unwrap();
+        let kubeconfigs = HashMap::from([("prod".to_string(), "apiVersion: v1".to_string())]);
+        let syncer = SecretSyncer::new(cluster.clone(), Arc::new(kubeconfigs), vault.clone(), policies);
+        (cluster, vault, syncer)
+    }
+
+    fn data(cluster: &FakeCluster, name: &str) -> KubeSecret {
+        cluster.secrets.lock().unwrap()[&("shop".to_string(), name.to_string())].clone()
+    }
+
+    #[tokio::test]
+    async fn test_sync_maps_values_and_propagates_rotation() {
+        let (cluster, vault, syncer) = setup().await;
+        let target = SyncTarget::new("shop-secrets", "prod", "shop", SecretFilter::all().with_label("sync", "shop"))
+            .with_name("shop/tls", "shop-tls");
+        syncer.add_target(target).await.unwrap();
+
+        let report = syncer.sync_all().await.remove(0).unwrap();
+        assert_eq!(report.outcome("shop-db"), Some(&SyncOutcome::Created));
+        assert!(matches!(report.outcome("billing-api"), Some(SyncOutcome::Denied(_))));
+        assert!(cluster.secrets.lock().unwrap().get(&("shop".to_string(), "billing-api".to_string())).is_none());
+
+        let db = data(&cluster, "shop-db");
+        assert_eq!(db.type_, "Opaque");
+        assert_eq!(db.data["password"], b"hunter2");
+        assert_eq!(db.annotations[SOURCE_ID_ANNOTATION], "shop/db");
+        let tls = data(&cluster, "shop-tls");
+        assert_eq!(tls.type_, "kubernetes.io/tls");
+        assert_eq!(tls.data["tls.crt"], b"-----LEAF-----\n-----INTERMEDIATE-----\n");
+        assert_eq!(tls.data["tls.key"], b"-----KEY-----");
+        assert_eq!(data(&cluster, "shop-keystore").data["value"], vec![0, 159, 255]);
+
+        let mut db = vault.get_secret("shop/db").await.unwrap();
+        db.value = SecretValue::Plain(r#"{"username":"shop","password":"rotated"}"#.into());
+        let db = vault.update_secret(db).await.unwrap();
+        let event = RotationEvent {
+            secret_id: "shop/db".into(),
+            old_version: db.version - 1,
+            new_version: db.version,
+            timestamp: Utc::now(),
+            triggered_by: "test".into(),
+            reason: RotationReason::Manual,
+        };
+        let report = syncer.on_rotation(&event).await.remove(0).unwrap();
+        assert_eq!(report.outcome("shop-db"), Some(&SyncOutcome::Updated));
+        assert_eq!(report.outcome("shop-tls"), Some(&SyncOutcome::Unchanged));
+        let synced = data(&cluster, "shop-db");
+        assert_eq!(synced.data["password"], b"rotated");
+        assert_eq!(synced.annotations[SOURCE_VERSION_ANNOTATION], db.version.to_string());
+    }
+
+    #[tokio::test]
+    async fn test_drift_is_corrected_and_deleted_sources_pruned() {
+        let (cluster, vault, syncer) = setup().await;
+        let target = SyncTarget::new("shop-secrets", "prod", "shop", SecretFilter::all().with_ids(vec!["shop/db".into(), "shop/tls".into()]));
+        syncer.add_target(target.clone()).await.unwrap();
+        syncer.sync_target(&target).await.unwrap();
+
+        // Someone edits the synced Secret by hand
+        cluster.secrets.lock().unwrap().get_mut(&("shop".to_string(), "shop-db".to_string())).unwrap().data.insert("password".into(), b"oops".to_vec());
+        let report = syncer.sync_target(&target).await.unwrap();
+        assert_eq!(report.outcome("shop-db"), Some(&SyncOutcome::DriftCorrected));
+        assert_eq!(data(&cluster, "shop-db").data["password"], b"hunter2");
+        assert_eq!(*cluster.events.lock().unwrap(), vec![("shop-db".to_string(), "SecretDriftCorrected".to_string())]);
+
+        // Without prune a deleted source leaves its copy alone; with it the copy goes
+        vault.delete_secret("shop/tls").await.unwrap();
+        syncer.sync_target(&target).await.unwrap();
+        assert!(cluster.secrets.lock().unwrap().contains_key(&("shop".to_string(), "shop-tls".to_string())));
+        let report = syncer.sync_target(&target.clone().with_prune(true)).await.unwrap();
+        assert_eq!(report.outcome("shop-tls"), Some(&SyncOutcome::Pruned));
+        assert_eq!(cluster.secrets.lock().unwrap().len(), 1);
+
+        // Secrets the syncer did not create are never overwritten
+        let sneaky = SyncTarget::new("other", "prod", "shop", SecretFilter::all().with_ids(vec!["shop/db".into()]));
+        let report = syncer.sync_target(&sneaky).await.unwrap();
+        assert!(matches!(report.outcome("shop-db"), Some(SyncOutcome::Failed(_))));
+    }
+}
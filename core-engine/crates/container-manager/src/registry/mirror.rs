@@ -0,0 +1,864 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use sirsi_key_vault::secret::{SecretManager, SecretValue};
+use sirsi_key_vault::KeyVaultError;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use super::{ImageReference, ImageTag};
+use crate::error::{ContainerError, ContainerResult};
+
+const OCI_MANIFEST: &str = "application/vnd.oci.image.manifest.v1+json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpstreamCredentials {
+    pub username: String,
+    // Key-vault secret holding the password or access token
+    pub secret_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Upstream {
+    // As written in image references, e.g. `docker.io`
+    pub registry: String,
+    // Where to fetch from, e.g. `registry-1.docker.io`
+    pub host: String,
+    pub credentials: Option<UpstreamCredentials>,
+}
+
+#[derive(Clone)]
+pub struct UpstreamAuth {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct UpstreamManifest {
+    pub media_type: String,
+    pub digest: String,
+    pub body: Vec<u8>,
+}
+
+// Registry calls a pull-through cache makes against an upstream
+#[async_trait]
+pub trait UpstreamClient: Send + Sync {
+    async fn manifest(
+        &self,
+        upstream: &Upstream,
+        auth: Option<&UpstreamAuth>,
+        repository: &str,
+        reference: &str,
+    ) -> ContainerResult<UpstreamManifest>;
+    // What a tag points at now, without downloading the manifest; registries don't count
+    // these against pull rate limits
+    async fn tag_digest(&self, upstream: &Upstream, auth: Option<&UpstreamAuth>, repository: &str, tag: &str) -> ContainerResult<String>;
+    async fn blob(&self, upstream: &Upstream, auth: Option<&UpstreamAuth>, repository: &str, digest: &str) -> ContainerResult<Vec<u8>>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoredBlob {
+    pub digest: String,
+    pub size: u64,
+}
+
+// Content-addressed storage for manifests and blobs alike
+#[async_trait]
+pub trait BlobStore: Send + Sync {
+    async fn put(&self, digest: &str, data: &[u8]) -> ContainerResult<()>;
+    async fn get(&self, digest: &str) -> ContainerResult<Option<Vec<u8>>>;
+    async fn delete(&self, digest: &str) -> ContainerResult<()>;
+    async fn list(&self) -> ContainerResult<Vec<StoredBlob>>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Pin {
+    pub image: String,
+    // The manifest the image resolved to; a pinned tag follows the tag on refresh
+    pub digest: String,
+    pub pinned_at: DateTime<Utc>,
+}
+
+#[async_trait]
+pub trait PinStore: Send + Sync {
+    async fn load_pins(&self) -> ContainerResult<Vec<Pin>>;
+    async fn save_pins(&self, pins: &[Pin]) -> ContainerResult<()>;
+}
+
+fn digest_hex(digest: &str) -> ContainerResult<&str> {
+    digest
+        .strip_prefix("sha256:")
+        .filter(|hex| hex.len() == 64 && hex.bytes().all(|b| b.is_ascii_hexdigit()))
+        .ok_or_else(|| ContainerError::Validation(format!("Unsupported digest: {}", digest)))
+}
+
+fn sha256_digest(data: &[u8]) -> String {
+    format!("sha256:{}", hex::encode(Sha256::digest(data)))
+}
+
+fn io_error(what: &str, e: std::io::Error) -> ContainerError {
+    ContainerError::Registry(format!("Failed to {}: {}", what, e))
+}
+
+// Keeps blobs under `<root>/blobs/sha256/<hex>`, written to a temporary file first so a crash
+// never leaves a truncated blob under its digest
+pub struct DiskBlobStore {
+    root: PathBuf,
+}
+
+impl DiskBlobStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn blobs(&self) -> PathBuf {
+        self.root.join("blobs").join("sha256")
+    }
+
+    fn path(&self, digest: &str) -> ContainerResult<PathBuf> {
+        Ok(self.blobs().join(digest_hex(digest)?))
+    }
+}
+
+#[async_trait]
+impl BlobStore for DiskBlobStore {
+    async fn put(&self, digest: &str, data: &[u8]) -> ContainerResult<()> {
+        let path = self.path(digest)?;
+        tokio::fs::create_dir_all(self.blobs()).await.map_err(|e| io_error("create the blob directory", e))?;
+        let partial = self.root.join(format!("partial-{}", uuid::Uuid::new_v4()));
+        tokio::fs::write(&partial, data).await.map_err(|e| io_error("write a blob", e))?;
+        tokio::fs::rename(&partial, &path).await.map_err(|e| io_error("store a blob", e))
+    }
+
+    async fn get(&self, digest: &str) -> ContainerResult<Option<Vec<u8>>> {
+        match tokio::fs::read(self.path(digest)?).await {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(io_error("read a blob", e)),
+        }
+    }
+
+    async fn delete(&self, digest: &str) -> ContainerResult<()> {
+        match tokio::fs::remove_file(self.path(digest)?).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(io_error("delete a blob", e)),
+            _ => Ok(()),
+        }
+    }
+
+    async fn list(&self) -> ContainerResult<Vec<StoredBlob>> {
+        let mut entries = match tokio::fs::read_dir(self.blobs()).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(io_error("list blobs", e)),
+        };
+        let mut blobs = Vec::new();
+        while let Some(entry) = entries.next_entry().await.map_err(|e| io_error("list blobs", e))? {
+            let size = entry.metadata().await.map_err(|e| io_error("read blob metadata", e))?.len();
+            blobs.push(StoredBlob { digest: format!("sha256:{}", entry.file_name().to_string_lossy()), size });
+        }
+        Ok(blobs)
+    }
+}
+
+// Pins live next to the blobs they protect
+#[async_trait]
+impl PinStore for DiskBlobStore {
+    async fn load_pins(&self) -> ContainerResult<Vec<Pin>> {
+        match tokio::fs::read(self.root.join("pins.json")).await {
+            Ok(data) => serde_json::from_slice(&data).map_err(|e| ContainerError::Registry(format!("Invalid pins file: {}", e))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(io_error("read pins", e)),
+        }
+    }
+
+    async fn save_pins(&self, pins: &[Pin]) -> ContainerResult<()> {
+        tokio::fs::create_dir_all(&self.root).await.map_err(|e| io_error("create the mirror directory", e))?;
+        let data = serde_json::to_vec_pretty(pins).map_err(|e| ContainerError::Internal(e.to_string()))?;
+        tokio::fs::write(self.root.join("pins.json"), data).await.map_err(|e| io_error("write pins", e))
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryBlobStore {
+    blobs: std::sync::Mutex<HashMap<String, Vec<u8>>>,
+}
+
+impl InMemoryBlobStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl BlobStore for InMemoryBlobStore {
+    async fn put(&self, digest: &str, data: &[u8]) -> ContainerResult<()> {
+        self.blobs.lock().unwrap().insert(digest.to_string(), data.to_vec());
+        Ok(())
+    }
+
+    async fn get(&self, digest: &str) -> ContainerResult<Option<Vec<u8>>> {
+        Ok(self.blobs.lock().unwrap().get(digest).cloned())
+    }
+
+    async fn delete(&self, digest: &str) -> ContainerResult<()> {
+        self.blobs.lock().unwrap().remove(digest);
+        Ok(())
+    }
+
+    async fn list(&self) -> ContainerResult<Vec<StoredBlob>> {
+        let blobs = self.blobs.lock().unwrap();
+        Ok(blobs.iter().map(|(digest, data)| StoredBlob { digest: digest.clone(), size: data.len() as u64 }).collect())
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryPinStore {
+    pins: std::sync::Mutex<Vec<Pin>>,
+}
+
+impl InMemoryPinStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl PinStore for InMemoryPinStore {
+    async fn load_pins(&self) -> ContainerResult<Vec<Pin>> {
+        Ok(self.pins.lock().unwrap().clone())
+    }
+
+    async fn save_pins(&self, pins: &[Pin]) -> ContainerResult<()> {
+        *self.pins.lock().unwrap() = pins.to_vec();
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MirrorConfig {
+    pub quota_bytes: u64,
+    // How long a tag is trusted before checking where it points upstream; digests never change
+    pub tag_ttl: Duration,
+}
+
+impl Default for MirrorConfig {
+    fn default() -> Self {
+        Self { quota_bytes: 50 * 1024 * 1024 * 1024, tag_ttl: Duration::from_secs(300) }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MirrorStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub hit_rate: f64,
+    // Served from the cache instead of the upstream
+    pub bytes_saved: u64,
+    pub bytes_fetched: u64,
+    pub evictions: u64,
+    // Fetched but not kept, because pinned images leave no room
+    pub uncached: u64,
+    pub used_bytes: u64,
+    pub quota_bytes: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServedManifest {
+    pub media_type: String,
+    pub digest: String,
+    pub body: Vec<u8>,
+}
+
+struct CachedObject {
+    size: u64,
+    last_used: u64,
+    media_type: Option<String>,
+    // Manifests and blobs a manifest refers to
+    children: Vec<String>,
+}
+
+struct CachedTag {
+    digest: String,
+    refreshed_at: DateTime<Utc>,
+}
+
+#[derive(Default)]
+struct MirrorState {
+    objects: HashMap<String, CachedObject>,
+    // By (registry, repository, tag)
+    tags: HashMap<(String, String, String), CachedTag>,
+    pins: Vec<Pin>,
+    used_bytes: u64,
+    clock: u64,
+    stats: MirrorStats,
+}
+
+impl MirrorState {
+    fn touch(&mut self, digest: &str) {
+        self.clock += 1;
+        if let Some(object) = self.objects.get_mut(digest) {
+            object.last_used = self.clock;
+        }
+    }
+
+    fn forget(&mut self, digest: &str) {
+        if let Some(object) = self.objects.remove(digest) {
+            self.used_bytes -= object.size;
+            self.tags.retain(|_, tag| tag.digest != digest);
+        }
+    }
+
+    // Everything reachable from a pin, including layers not fetched yet
+    fn protected(&self) -> HashSet<String> {
+        let mut protected = HashSet::new();
+        let mut pending: Vec<String> = self.pins.iter().map(|p| p.digest.clone()).collect();
+        while let Some(digest) = pending.pop() {
+            if let Some(object) = self.objects.get(&digest) {
+                pending.extend(object.children.iter().filter(|c| !protected.contains(*c)).cloned());
+            }
+            protected.insert(digest);
+        }
+        protected
+    }
+
+    // Least recently used first, never pinned content; `None` if the object can't fit
+    fn eviction_for(&self, size: u64, quota: u64) -> Option<Vec<String>> {
+        let protected = self.protected();
+        let mut candidates: Vec<(&String, &CachedObject)> = self.objects.iter().filter(|(d, _)| !protected.contains(*d)).collect();
+        candidates.sort_by_key(|(_, o)| o.last_used);
+        let mut used = self.used_bytes;
+        let mut victims = Vec::new();
+        for (digest, object) in candidates {
+            if used + size <= quota {
+                break;
+            }
+            used -= object.size;
+            victims.push(digest.clone());
+        }
+        (used + size <= quota).then_some(victims)
+    }
+}
+
+// Manifests an index lists, then the config and layers a manifest needs
+fn manifest_references(body: &[u8]) -> (Vec<String>, Vec<String>) {
+    let Ok(manifest) = serde_json::from_slice::<Value>(body) else { return Default::default() };
+    let digests = |entries: Option<&Value>| -> Vec<String> {
+        entries
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|d| d.get("digest").and_then(Value::as_str).map(str::to_string))
+            .collect()
+    };
+    let mut blobs: Vec<String> = manifest.pointer("/config/digest").and_then(Value::as_str).map(str::to_string).into_iter().collect();
+    blobs.extend(digests(manifest.get("layers")));
+    (digests(manifest.get("manifests")), blobs)
+}
+
+fn manifest_media_type(body: &[u8]) -> String {
+    serde_json::from_slice::<Value>(body)
+        .ok()
+        .and_then(|m| m.get("mediaType").and_then(Value::as_str).map(str::to_string))
+        .unwrap_or_else(|| OCI_MANIFEST.to_string())
+}
+
+// Pull-through cache in front of upstream registries. Blobs and manifests are kept by digest
+// and served forever; tags map to digests and are re-checked upstream once their TTL passes.
+pub struct RegistryMirror {
+    client: Arc<dyn UpstreamClient>,
+    store: Arc<dyn BlobStore>,
+    pin_store: Arc<dyn PinStore>,
+    secrets: Option<Arc<dyn SecretManager>>,
+    upstreams: HashMap<String, Upstream>,
+    config: MirrorConfig,
+    state: Mutex<MirrorState>,
+}
+
+impl RegistryMirror {
+    pub fn new(client: Arc<dyn UpstreamClient>, store: Arc<dyn BlobStore>, config: MirrorConfig) -> Self {
+        Self {
+            client,
+            store,
+            pin_store: Arc::new(InMemoryPinStore::new()),
+            secrets: None,
+            upstreams: HashMap::new(),
+            config,
+            state: Mutex::new(MirrorState::default()),
+        }
+    }
+
+    pub fn with_upstream(mut self, upstream: Upstream) -> Self {
+        self.upstreams.insert(upstream.registry.clone(), upstream);
+        self
+    }
+
+    pub fn with_pin_store(mut self, pin_store: Arc<dyn PinStore>) -> Self {
+        self.pin_store = pin_store;
+        self
+    }
+
+    // Where upstream credentials are read from
+    pub fn with_secrets(mut self, secrets: Arc<dyn SecretManager>) -> Self {
+        self.secrets = Some(secrets);
+        self
+    }
+
+    pub fn serves(&self, registry: &str) -> bool {
+        self.upstreams.contains_key(registry)
+    }
+
+    // Picks up what an earlier run left in the store. Recency isn't kept across restarts, so
+    // everything found starts out equally old.
+    pub async fn load(&self) -> ContainerResult<()> {
+        let blobs = self.store.list().await?;
+        let pins = self.pin_store.load_pins().await?;
+        let mut references = HashMap::new();
+        let mut pending: Vec<String> = pins.iter().map(|p| p.digest.clone()).collect();
+        while let Some(digest) = pending.pop() {
+            if references.contains_key(&digest) {
+                continue;
+            }
+            if let Some(body) = self.store.get(&digest).await? {
+                let (manifests, blobs) = manifest_references(&body);
+                pending.extend(manifests.iter().cloned());
+                references.insert(digest, (manifest_media_type(&body), [manifests, blobs].concat()));
+            }
+        }
+
+        let mut state = self.state.lock().await;
+        for blob in blobs {
+            let (media_type, children) = references.remove(&blob.digest).map_or((None, Vec::new()), |(m, c)| (Some(m), c));
+            if state.objects.insert(blob.digest, CachedObject { size: blob.size, last_used: 0, media_type, children }).is_none() {
+                state.used_bytes += blob.size;
+            }
+        }
+        state.pins = pins;
+        info!(objects = state.objects.len(), bytes = state.used_bytes, "Loaded registry mirror");
+        Ok(())
+    }
+
+    fn upstream(&self, registry: &str) -> ContainerResult<&Upstream> {
+        self.upstreams
+            .get(registry)
+            .ok_or_else(|| ContainerError::NotFound(format!("No upstream configured for {}", registry)))
+    }
+
+    async fn auth(&self, upstream: &Upstream) -> ContainerResult<Option<UpstreamAuth>> {
+        let Some(credentials) = &upstream.credentials else { return Ok(None) };
+        let secrets = self.secrets.as_ref().ok_or_else(|| {
+            ContainerError::Config(format!("Upstream {} has credentials but no key vault is configured", upstream.registry))
+        })?;
+        let secret = secrets.get_secret(&credentials.secret_id).await.map_err(|e| match e {
+            KeyVaultError::NotFound(_) => ContainerError::NotFound(format!("Registry credential secret {} not found", credentials.secret_id)),
+            KeyVaultError::Permission(msg) => ContainerError::Permission(msg),
+            e => ContainerError::Internal(format!("Failed to read registry credential secret {}: {}", credentials.secret_id, e)),
+        })?;
+        match secret.value {
+            SecretValue::Plain(password) => Ok(Some(UpstreamAuth { username: credentials.username.clone(), password })),
+            _ => Err(ContainerError::Config(format!("Secret {} is not a registry password", credentials.secret_id))),
+        }
+    }
+
+    async fn cached(&self, digest: &str) -> ContainerResult<Option<(Vec<u8>, Option<String>)>> {
+        let Some(media_type) = self.state.lock().await.objects.get(digest).map(|o| o.media_type.clone()) else { return Ok(None) };
+        let data = self.store.get(digest).await?;
+        let mut state = self.state.lock().await;
+        match data {
+            Some(data) => {
+                state.touch(digest);
+                state.stats.hits += 1;
+                state.stats.bytes_saved += data.len() as u64;
+                Ok(Some((data, media_type)))
+            }
+            // Removed from the store behind our back
+            None => {
+                state.forget(digest);
+                Ok(None)
+            }
+        }
+    }
+
+    async fn insert(&self, digest: &str, data: &[u8], media_type: Option<String>) -> ContainerResult<()> {
+        let children = if media_type.is_some() {
+            let (manifests, blobs) = manifest_references(data);
+            [manifests, blobs].concat()
+        } else {
+            Vec::new()
+        };
+        self.store.put(digest, data).await?;
+        let size = data.len() as u64;
+        let (victims, kept) = {
+            let mut state = self.state.lock().await;
+            if state.objects.contains_key(digest) {
+                state.touch(digest);
+                return Ok(());
+            }
+            match state.eviction_for(size, self.config.quota_bytes) {
+                Some(victims) => {
+                    for victim in &victims {
+                        state.forget(victim);
+                    }
+                    state.stats.evictions += victims.len() as u64;
+                    state.objects.insert(digest.to_string(), CachedObject { size, last_used: 0, media_type, children });
+                    state.used_bytes += size;
+                    state.touch(digest);
+                    (victims, true)
+                }
+                None => {
+                    state.stats.uncached += 1;
+                    (Vec::new(), false)
+                }
+            }
+        };
+        if !kept {
+            warn!(digest, size, "Pinned images leave no room to cache object");
+            self.store.delete(digest).await?;
+        }
+        for victim in victims {
+            if let Err(e) = self.store.delete(&victim).await {
+                warn!("Failed to delete evicted object {}: {}", victim, e);
+            }
+        }
+        Ok(())
+    }
+
+    async fn fetch_manifest(&self, upstream: &Upstream, repository: &str, reference: &str) -> ContainerResult<UpstreamManifest> {
+        let auth = self.auth(upstream).await?;
+        let manifest = self.client.manifest(upstream, auth.as_ref(), repository, reference).await?;
+        let digest = sha256_digest(&manifest.body);
+        if digest != manifest.digest || (reference.starts_with("sha256:") && reference != digest) {
+            return Err(ContainerError::Registry(format!("Manifest {}@{} does not match its digest", repository, reference)));
+        }
+        self.insert(&digest, &manifest.body, Some(manifest.media_type.clone())).await?;
+        Ok(manifest)
+    }
+
+    pub async fn manifest(&self, image: &ImageReference) -> ContainerResult<ServedManifest> {
+        let upstream = self.upstream(&image.registry)?;
+        let tag_key = |tag: &str| (image.registry.clone(), image.repository.clone(), tag.to_string());
+        let digest = match &image.reference {
+            ImageTag::Digest(digest) => Some(digest_hex(digest).map(|_| digest.clone())?),
+            ImageTag::Tag(tag) => self.state.lock().await.tags.get(&tag_key(tag)).map(|t| t.digest.clone()),
+        };
+        if let Some(digest) = digest {
+            if let Some((body, media_type)) = self.cached(&digest).await? {
+                let media_type = media_type.unwrap_or_else(|| manifest_media_type(&body));
+                return Ok(ServedManifest { media_type, digest, body });
+            }
+        }
+
+        let manifest = self.fetch_manifest(upstream, &image.repository, image.reference.as_str()).await?;
+        let mut state = self.state.lock().await;
+        state.stats.misses += 1;
+        state.stats.bytes_fetched += manifest.body.len() as u64;
+        if let ImageTag::Tag(tag) = &image.reference {
+            state.tags.insert(tag_key(tag), CachedTag { digest: manifest.digest.clone(), refreshed_at: Utc::now() });
+        }
+        Ok(ServedManifest { media_type: manifest.media_type, digest: manifest.digest, body: manifest.body })
+    }
+
+    pub async fn blob(&self, registry: &str, repository: &str, digest: &str) -> ContainerResult<Vec<u8>> {
+        let upstream = self.upstream(registry)?;
+        digest_hex(digest)?;
+        if let Some((data, _)) = self.cached(digest).await? {
+            return Ok(data);
+        }
+        let auth = self.auth(upstream).await?;
+        let data = self.client.blob(upstream, auth.as_ref(), repository, digest).await?;
+        if sha256_digest(&data) != digest {
+            return Err(ContainerError::Registry(format!("Blob {}@{} does not match its digest", repository, digest)));
+        }
+        {
+            let mut state = self.state.lock().await;
+            state.stats.misses += 1;
+            state.stats.bytes_fetched += data.len() as u64;
+        }
+        self.insert(digest, &data, None).await?;
+        Ok(data)
+    }
+
+    // Keeps an image out of eviction. Its manifests are fetched now so the layers they list are
+    // known; the layers themselves are cached on first pull.
+    pub async fn pin(&self, image: &str) -> ContainerResult<Pin> {
+        let reference = ImageReference::parse(image)?;
+        let manifest = self.manifest(&reference).await?;
+        for child in manifest_references(&manifest.body).0 {
+            self.manifest(&ImageReference { reference: ImageTag::Digest(child), ..reference.clone() }).await?;
+        }
+        let pin = Pin { image: reference.to_string(), digest: manifest.digest, pinned_at: Utc::now() };
+        let mut state = self.state.lock().await;
+        state.pins.retain(|p| p.image != pin.image);
+        state.pins.push(pin.clone());
+        self.pin_store.save_pins(&state.pins).await?;
+        info!(image = %pin.image, digest = %pin.digest, "Pinned image in registry mirror");
+        Ok(pin)
+    }
+
+    pub async fn unpin(&self, image: &str) -> ContainerResult<()> {
+        let image = ImageReference::parse(image)?.to_string();
+        let mut state = self.state.lock().await;
+        let before = state.pins.len();
+        state.pins.retain(|p| p.image != image);
+        if state.pins.len() == before {
+            return Err(ContainerError::NotFound(format!("Image {} is not pinned", image)));
+        }
+        self.pin_store.save_pins(&state.pins).await
+    }
+
+    pub async fn pins(&self) -> Vec<Pin> {
+        self.state.lock().await.pins.clone()
+    }
+
+    pub async fn stats(&self) -> MirrorStats {
+        let state = self.state.lock().await;
+        let requests = state.stats.hits + state.stats.misses;
+        MirrorStats {
+            hit_rate: if requests == 0 { 0.0 } else { state.stats.hits as f64 / requests as f64 },
+            used_bytes: state.used_bytes,
+            quota_bytes: self.config.quota_bytes,
+            ..state.stats.clone()
+        }
+    }
+
+    async fn refresh_tag(&self, registry: &str, repository: &str, tag: &str, cached: &str) -> ContainerResult<String> {
+        let upstream = self.upstream(registry)?;
+        let auth = self.auth(upstream).await?;
+        let current = self.client.tag_digest(upstream, auth.as_ref(), repository, tag).await?;
+        if current != cached {
+            self.fetch_manifest(upstream, repository, &current).await?;
+        }
+        Ok(current)
+    }
+
+    // Re-resolves tags past their TTL and returns how many moved. A tag that can't be
+    // refreshed keeps serving what it pointed at.
+    pub async fn refresh_tags(&self) -> usize {
+        let ttl = chrono::Duration::from_std(self.config.tag_ttl).unwrap_or(chrono::Duration::MAX);
+        let now = Utc::now();
+        let due: Vec<((String, String, String), String)> = {
+            let state = self.state.lock().await;
+            state.tags.iter().filter(|(_, t)| t.refreshed_at + ttl <= now).map(|(k, t)| (k.clone(), t.digest.clone())).collect()
+        };
+        let mut moved = 0;
+        for ((registry, repository, tag), cached) in due {
+            let current = match self.refresh_tag(&registry, &repository, &tag, &cached).await {
+                Ok(current) => current,
+                Err(e) => {
+                    warn!("Failed to refresh {}/{}:{}: {}", registry, repository, tag, e);
+                    continue;
+                }
+            };
+            let mut state = self.state.lock().await;
+            state.tags.insert((registry.clone(), repository.clone(), tag.clone()), CachedTag { digest: current.clone(), refreshed_at: now });
+            if current != cached {
+                moved += 1;
+                info!(registry, repository, tag, digest = %current, "Tag moved upstream");
+                let image = ImageReference { registry, repository, reference: ImageTag::Tag(tag) }.to_string();
+                if let Some(pin) = state.pins.iter_mut().find(|p| p.image == image) {
+                    pin.digest = current;
+                    if let Err(e) = self.pin_store.save_pins(&state.pins).await {
+                        warn!("Failed to save pins: {}", e);
+                    }
+                }
+            }
+        }
+        moved
+    }
+
+    pub fn spawn_refresher(self: Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                self.refresh_tags().await;
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex as StdMutex;
+
+    use serde_json::json;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct FakeUpstream {
+        manifests: StdMutex<HashMap<String, Vec<u8>>>,
+        tags: StdMutex<HashMap<String, String>>,
+        blobs: StdMutex<HashMap<String, Vec<u8>>>,
+        calls: StdMutex<Vec<String>>,
+    }
+
+    impl FakeUpstream {
+        fn add_blob(&self, data: &[u8]) -> String {
+            let digest = sha256_digest(data);
+            self.blobs.lock().unwrap().insert(digest.clone(), data.to_vec());
+            digest
+        }
+
+        // Tags `repository:tag` with a manifest over the given layers and returns its digest
+        fn add_image(&self, repository: &str, tag: &str, layers: &[&[u8]]) -> String {
+            let config = self.add_blob(format!("{{\"tag\":\"{}\"}}", tag).as_bytes());
+            let layers: Vec<Value> = layers.iter().map(|l| json!({ "digest": self.add_blob(l), "size": l.len() })).collect();
+            let body = serde_json::to_vec(&json!({ "mediaType": OCI_MANIFEST, "config": { "digest": config }, "layers": layers })).unwrap();
+            let digest = sha256_digest(&body);
+            self.manifests.lock().unwrap().insert(digest.clone(), body);
+            self.tags.lock().unwrap().insert(format!("{}:{}", repository, tag), digest.clone());
+            digest
+        }
+
+        fn calls(&self) -> Vec<String> {
+            std::mem::take(&mut *self.calls.lock().unwrap())
+        }
+    }
+
+    #[async_trait]
+    impl UpstreamClient for FakeUpstream {
+        async fn manifest(&self, _: &Upstream, _: Option<&UpstreamAuth>, repository: &str, reference: &str) -> ContainerResult<UpstreamManifest> {
+            self.calls.lock().unwrap().push(format!("manifest {}", reference));
+            let digest = match reference.starts_with("sha256:") {
+                true => reference.to_string(),
+                false => self.tags.lock().unwrap().get(&format!("{}:{}", repository, reference)).cloned().unwrap_or_default(),
+            };
+            let body = self.manifests.lock().unwrap().get(&digest).cloned().ok_or_else(|| ContainerError::NotFound(reference.into()))?;
+            Ok(UpstreamManifest { media_type: OCI_MANIFEST.into(), digest, body })
+        }
+
+        async fn tag_digest(&self, _: &Upstream, _: Option<&UpstreamAuth>, repository: &str, tag: &str) -> ContainerResult<String> {
+            self.calls.lock().unwrap().push(format!("head {}", tag));
+            Ok(self.tags.lock().unwrap()[&format!("{}:{}", repository, tag)].clone())
+        }
+
+        async fn blob(&self, _: &Upstream, _: Option<&UpstreamAuth>, _: &str, digest: &str) -> ContainerResult<Vec<u8>> {
+            self.calls.lock().unwrap().push(format!("blob {}", &digest[7..15]));
+            self.blobs.lock().unwrap().get(digest).cloned().ok_or_else(|| ContainerError::NotFound(digest.into()))
+        }
+    }
+
+    fn mirror(upstream: Arc<FakeUpstream>, store: Arc<InMemoryBlobStore>, quota_bytes: u64, tag_ttl: Duration) -> RegistryMirror {
+        let hub = Upstream { registry: "docker.io".into(), host: "registry-1.docker.io".into(), credentials: None };
+        RegistryMirror::new(upstream, store, MirrorConfig { quota_bytes, tag_ttl }).with_upstream(hub)
+    }
+
+    #[tokio::test]
+    async fn test_miss_then_hit_and_tag_refresh() {
+        let upstream = Arc::new(FakeUpstream::default());
+        let first = upstream.add_image("library/nginx", "1.25", &[b"nginx layer one"]);
+        let store = Arc::new(InMemoryBlobStore::new());
+        let mirror = mirror(upstream.clone(), store.clone(), 1 << 20, Duration::ZERO);
+        let nginx = ImageReference::parse("nginx:1.25").unwrap();
+
+        let served = mirror.manifest(&nginx).await.unwrap();
+        assert_eq!(served.digest, first);
+        let layer = manifest_references(&served.body).1[1].clone();
+        assert_eq!(mirror.blob("docker.io", "library/nginx", &layer).await.unwrap(), b"nginx layer one");
+        assert_eq!(upstream.calls(), vec!["manifest 1.25".to_string(), format!("blob {}", &layer[7..15])]);
+
+        // Second pull never reaches the upstream
+        assert_eq!(mirror.manifest(&nginx).await.unwrap(), served);
+        mirror.blob("docker.io", "library/nginx", &layer).await.unwrap();
+        assert!(upstream.calls().is_empty());
+        let stats = mirror.stats().await;
+        assert_eq!((stats.hits, stats.misses, stats.hit_rate), (2, 2, 0.5));
+        assert_eq!(stats.bytes_saved, served.body.len() as u64 + 15);
+        assert_eq!(stats.used_bytes, stats.bytes_fetched);
+
+        // The tag moves upstream: pulls serve the old digest until the refresh picks it up
+        let second = upstream.add_image("library/nginx", "1.25", &[b"nginx layer two"]);
+        upstream.calls();
+        assert_eq!(mirror.manifest(&nginx).await.unwrap().digest, first);
+        assert_eq!(mirror.refresh_tags().await, 1);
+        assert_eq!(upstream.calls(), vec!["head 1.25".to_string(), format!("manifest {}", second)]);
+        assert_eq!(mirror.manifest(&nginx).await.unwrap().digest, second);
+        assert_eq!(mirror.refresh_tags().await, 0);
+
+        // Digests are immutable, so the old one is still served without asking the upstream
+        let by_digest = ImageReference { reference: ImageTag::Digest(first.clone()), ..nginx.clone() };
+        assert_eq!(mirror.manifest(&by_digest).await.unwrap().digest, first);
+        assert_eq!(upstream.calls(), vec!["head 1.25".to_string()]);
+
+        let missing = sha256_digest(b"never uploaded");
+        assert!(matches!(mirror.blob("docker.io", "library/nginx", &missing).await, Err(ContainerError::NotFound(_))));
+        assert!(matches!(mirror.blob("docker.io", "library/nginx", "sha256:../../etc").await, Err(ContainerError::Validation(_))));
+        assert!(matches!(mirror.blob("quay.io", "x/y", &missing).await, Err(ContainerError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_quota_evicts_least_recently_used() {
+        let upstream = Arc::new(FakeUpstream::default());
+        let [a, b, c] = [[b'a'; 40], [b'b'; 40], [b'c'; 40]].map(|data| upstream.add_blob(&data));
+        let store = Arc::new(InMemoryBlobStore::new());
+        let mirror = mirror(upstream.clone(), store.clone(), 100, Duration::from_secs(300));
+
+        for digest in [&a, &b, &a] {
+            mirror.blob("docker.io", "library/busybox", digest).await.unwrap();
+        }
+        // Holding a and b, a used last; c needs room and b goes
+        mirror.blob("docker.io", "library/busybox", &c).await.unwrap();
+        let mut stored: Vec<String> = store.list().await.unwrap().into_iter().map(|b| b.digest).collect();
+        stored.sort();
+        let mut expected = vec![a.clone(), c.clone()];
+        expected.sort();
+        assert_eq!(stored, expected);
+        let stats = mirror.stats().await;
+        assert_eq!((stats.evictions, stats.used_bytes), (1, 80));
+
+        mirror.blob("docker.io", "library/busybox", &b).await.unwrap();
+        assert!(store.get(&a).await.unwrap().is_none());
+        assert_eq!(mirror.stats().await.evictions, 2);
+    }
+
+    #[tokio::test]
+    async fn test_pinned_images_survive_eviction() {
+        let upstream = Arc::new(FakeUpstream::default());
+        let agent = upstream.add_image("sirsi/agent", "stable", &[&[b'x'; 60]]);
+        let store = Arc::new(InMemoryBlobStore::new());
+        let pins = Arc::new(InMemoryPinStore::new());
+        let body_size = upstream.manifests.lock().unwrap()[&agent].len() as u64;
+        let config_size = b"{\"tag\":\"stable\"}".len() as u64;
+        let pinned_size = body_size + config_size + 60;
+        let mirror = mirror(upstream.clone(), store.clone(), pinned_size + 50, Duration::from_secs(300)).with_pin_store(pins.clone());
+
+        let pin = mirror.pin("sirsi/agent:stable").await.unwrap();
+        assert_eq!(pin.image, "docker.io/sirsi/agent:stable");
+        assert_eq!(pins.load_pins().await.unwrap(), vec![pin]);
+        let (_, blobs) = manifest_references(&upstream.manifests.lock().unwrap()[&agent]);
+        for blob in &blobs {
+            mirror.blob("docker.io", "sirsi/agent", blob).await.unwrap();
+        }
+
+        // Unpinned content churns through the 50 bytes left; the pinned image never leaves
+        let others: Vec<String> = (0..4u8).map(|i| upstream.add_blob(&[i; 40])).collect();
+        for digest in &others {
+            mirror.blob("docker.io", "library/redis", digest).await.unwrap();
+        }
+        for digest in blobs.iter().chain([&agent]) {
+            assert!(store.get(digest).await.unwrap().is_some());
+        }
+        assert_eq!(mirror.stats().await.evictions, 3);
+
+        // Too big to fit beside the pinned image: served, just not kept
+        let large = upstream.add_blob(&[7; 80]);
+        assert_eq!(mirror.blob("docker.io", "library/redis", &large).await.unwrap().len(), 80);
+        assert!(store.get(&large).await.unwrap().is_none());
+        assert_eq!(mirror.stats().await.uncached, 1);
+
+        mirror.unpin("docker.io/sirsi/agent:stable").await.unwrap();
+        assert!(mirror.unpin("sirsi/agent:stable").await.is_err());
+        // Once unpinned its manifest is simply the oldest object
+        mirror.blob("docker.io", "library/redis", &large).await.unwrap();
+        assert!(store.get(&large).await.unwrap().is_some());
+        assert!(store.get(&agent).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,102 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{ContainerError, ContainerResult};
+
+pub mod mirror;
+mod oci;
+pub mod server;
+
+pub use mirror::{
+    BlobStore, DiskBlobStore, InMemoryBlobStore, InMemoryPinStore, MirrorConfig, MirrorStats, PinStore, RegistryMirror,
+    ServedManifest, StoredBlob, Upstream, UpstreamAuth, UpstreamClient, UpstreamCredentials, UpstreamManifest,
+};
+pub use oci::{OciIndexPublisher, OciUpstreamClient};
+
+pub const DEFAULT_REGISTRY: &str = "docker.io";
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImageTag {
+    Tag(String),
+    Digest(String),
+}
+
+impl ImageTag {
+    pub fn as_str(&self) -> &str {
+        match self {
+            ImageTag::Tag(tag) => tag,
+            ImageTag::Digest(digest) => digest,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ImageReference {
+    pub registry: String,
+    pub repository: String,
+    pub reference: ImageTag,
+}
+
+impl ImageReference {
+    // Docker's shorthand applies: `nginx` is `docker.io/library/nginx:latest`
+    pub fn parse(image: &str) -> ContainerResult<Self> {
+        let invalid = || ContainerError::Validation(format!("Invalid image reference: {}", image));
+        let (name, reference) = match image.split_once('@') {
+            Some((name, digest)) if digest.starts_with("sha256:") => (name, ImageTag::Digest(digest.to_string())),
+            Some(_) => return Err(invalid()),
+            None => match image.rsplit_once(':').filter(|(_, tag)| !tag.contains('/')) {
+                Some((name, tag)) => (name, ImageTag::Tag(tag.to_string())),
+                None => (image, ImageTag::Tag("latest".to_string())),
+            },
+        };
+        let (registry, repository) = match name.split_once('/') {
+            Some((host, rest)) if host.contains('.') || host.contains(':') || host == "localhost" => (host.to_string(), rest.to_string()),
+            _ => (DEFAULT_REGISTRY.to_string(), name.to_string()),
+        };
+        let repository = if registry == DEFAULT_REGISTRY && !repository.contains('/') { format!("library/{}", repository) } else { repository };
+        if repository.is_empty() || reference.as_str().is_empty() {
+            return Err(invalid());
+        }
+        Ok(Self { registry, repository, reference })
+    }
+
+    // How the image is pulled through a mirror at `mirror`, which takes the upstream registry
+    // as the first path segment
+    pub fn mirrored(&self, mirror: &str) -> String {
+        let reference = ImageReference { registry: format!("{}/{}", mirror, self.registry), ..self.clone() };
+        reference.to_string()
+    }
+}
+
+impl fmt::Display for ImageReference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.reference {
+            ImageTag::Tag(tag) => write!(f, "{}/{}:{}", self.registry, self.repository, tag),
+            ImageTag::Digest(digest) => write!(f, "{}/{}@{}", self.registry, self.repository, digest),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_image_references() {
+        let nginx = ImageReference::parse("nginx").unwrap();
+        assert_eq!(nginx.to_string(), "docker.io/library/nginx:latest");
+        assert_eq!(nginx.mirrored("mirror.internal:5000"), "mirror.internal:5000/docker.io/library/nginx:latest");
+
+        let pinned = ImageReference::parse("ghcr.io/sirsi/agent@sha256:9f86d08").unwrap();
+        assert_eq!(pinned.registry, "ghcr.io");
+        assert_eq!(pinned.repository, "sirsi/agent");
+        assert_eq!(pinned.reference, ImageTag::Digest("sha256:9f86d08".into()));
+
+        let local = ImageReference::parse("localhost:5000/tools/rsync:3.2").unwrap();
+        assert_eq!((local.registry.as_str(), local.reference.as_str()), ("localhost:5000", "3.2"));
+        assert_eq!(ImageReference::parse("bitnami/redis:7").unwrap().to_string(), "docker.io/bitnami/redis:7");
+        assert!(ImageReference::parse("nginx@latest").is_err());
+    }
+}
@@ -0,0 +1,179 @@
+use async_trait::async_trait;
+use oci_distribution::client::{Client, ClientConfig};
+use oci_distribution::manifest::{ImageIndexEntry, OciImageIndex, Platform, OCI_IMAGE_INDEX_MEDIA_TYPE};
+use oci_distribution::secrets::RegistryAuth;
+use oci_distribution::{Reference, RegistryOperation};
+
+use super::mirror::{Upstream, UpstreamAuth, UpstreamClient, UpstreamManifest};
+use crate::error::{ContainerError, ContainerResult};
+use crate::service::build::{IndexPublisher, PlatformBuild};
+
+const MANIFEST_MEDIA_TYPES: &[&str] = &[
+    "application/vnd.oci.image.index.v1+json",
+    "application/vnd.oci.image.manifest.v1+json",
+    "application/vnd.docker.distribution.manifest.list.v2+json",
+    "application/vnd.docker.distribution.manifest.v2+json",
+];
+
+// Talks to upstreams over the distribution API, keeping bearer tokens between calls
+pub struct OciUpstreamClient {
+    client: Client,
+}
+
+impl Default for OciUpstreamClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OciUpstreamClient {
+    pub fn new() -> Self {
+        Self { client: Client::new(ClientConfig::default()) }
+    }
+
+    fn reference(upstream: &Upstream, repository: &str, reference: &str) -> Reference {
+        match reference.starts_with("sha256:") {
+            true => Reference::with_digest(upstream.host.clone(), repository.to_string(), reference.to_string()),
+            false => Reference::with_tag(upstream.host.clone(), repository.to_string(), reference.to_string()),
+        }
+    }
+
+    fn auth(auth: Option<&UpstreamAuth>) -> RegistryAuth {
+        match auth {
+            Some(auth) => RegistryAuth::Basic(auth.username.clone(), auth.password.clone()),
+            None => RegistryAuth::Anonymous,
+        }
+    }
+}
+
+fn upstream_error(what: &str, e: oci_distribution::errors::OciDistributionError) -> ContainerError {
+    let message = e.to_string();
+    if message.contains("404") || message.contains("MANIFEST_UNKNOWN") || message.contains("BLOB_UNKNOWN") {
+        ContainerError::NotFound(format!("{}: {}", what, message))
+    } else if message.contains("401") || message.contains("403") || message.contains("DENIED") {
+        ContainerError::Permission(format!("{}: {}", what, message))
+    } else {
+        ContainerError::Registry(format!("Failed to fetch {}: {}", what, message))
+    }
+}
+
+#[async_trait]
+impl UpstreamClient for OciUpstreamClient {
+    async fn manifest(
+        &self,
+        upstream: &Upstream,
+        auth: Option<&UpstreamAuth>,
+        repository: &str,
+        reference: &str,
+    ) -> ContainerResult<UpstreamManifest> {
+        let image = Self::reference(upstream, repository, reference);
+        let (body, digest) = self
+            .client
+            .pull_manifest_raw(&image, &Self::auth(auth), MANIFEST_MEDIA_TYPES)
+            .await
+            .map_err(|e| upstream_error(&image.whole(), e))?;
+        let media_type = serde_json::from_slice::<serde_json::Value>(&body)
+            .ok()
+            .and_then(|m| m.get("mediaType").and_then(|t| t.as_str()).map(str::to_string))
+            .unwrap_or_else(|| MANIFEST_MEDIA_TYPES[1].to_string());
+        Ok(UpstreamManifest { media_type, digest, body })
+    }
+
+    async fn tag_digest(&self, upstream: &Upstream, auth: Option<&UpstreamAuth>, repository: &str, tag: &str) -> ContainerResult<String> {
+        let image = Self::reference(upstream, repository, tag);
+        self.client
+            .fetch_manifest_digest(&image, &Self::auth(auth))
+            .await
+            .map_err(|e| upstream_error(&image.whole(), e))
+    }
+
+    async fn blob(&self, upstream: &Upstream, auth: Option<&UpstreamAuth>, repository: &str, digest: &str) -> ContainerResult<Vec<u8>> {
+        let image = Self::reference(upstream, repository, digest);
+        self.client
+            .auth(&image, &Self::auth(auth), RegistryOperation::Pull)
+            .await
+            .map_err(|e| upstream_error(&image.whole(), e))?;
+        let mut data = Vec::new();
+        self.client
+            .pull_blob(&image, digest, &mut data)
+            .await
+            .map_err(|e| upstream_error(&image.whole(), e))?;
+        Ok(data)
+    }
+}
+
+// Assembles multi-platform images in our own registry from manifests BuildKit pushed by digest
+pub struct OciIndexPublisher {
+    client: Client,
+    auth: RegistryAuth,
+}
+
+impl Default for OciIndexPublisher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OciIndexPublisher {
+    pub fn new() -> Self {
+        Self { client: Client::new(ClientConfig::default()), auth: RegistryAuth::Anonymous }
+    }
+
+    pub fn with_basic_auth(mut self, username: impl Into<String>, password: impl Into<String>) -> Self {
+        self.auth = RegistryAuth::Basic(username.into(), password.into());
+        self
+    }
+}
+
+#[async_trait]
+impl IndexPublisher for OciIndexPublisher {
+    async fn push_index(&self, name: &str, tag: &str, manifests: &[PlatformBuild]) -> ContainerResult<String> {
+        let (registry, repository) = name
+            .split_once('/')
+            .ok_or_else(|| ContainerError::Validation(format!("Image name {} has no registry", name)))?;
+        let image = Reference::with_tag(registry.to_string(), repository.to_string(), tag.to_string());
+        let manifests = manifests
+            .iter()
+            .map(|build| {
+                let mut parts = build.platform.splitn(3, '/');
+                let os = parts.next().unwrap_or_default().to_string();
+                let architecture = parts.next().unwrap_or_default().to_string();
+                ImageIndexEntry {
+                    media_type: build.manifest.media_type.clone(),
+                    digest: build.manifest.digest.clone(),
+                    size: build.manifest.size as i64,
+                    platform: Some(Platform {
+                        architecture,
+                        os,
+                        os_version: None,
+                        os_features: None,
+                        variant: parts.next().map(str::to_string),
+                        features: None,
+                    }),
+                    annotations: None,
+                }
+            })
+            .collect();
+        let index = OciImageIndex {
+            schema_version: 2,
+            media_type: Some(OCI_IMAGE_INDEX_MEDIA_TYPE.to_string()),
+            manifests,
+            annotations: None,
+        };
+        self.client
+            .auth(&image, &self.auth, RegistryOperation::Push)
+            .await
+            .map_err(|e| upstream_error(&image.whole(), e))?;
+        let url = self
+            .client
+            .push_manifest_list(&image, &self.auth, index)
+            .await
+            .map_err(|e| upstream_error(&image.whole(), e))?;
+        // The registry answers with the manifest's location, which ends in its digest
+        url.rsplit('/')
+            .next()
+            .filter(|digest| digest.starts_with("sha256:"))
+            .map(str::to_string)
+            .ok_or_else(|| ContainerError::Registry(format!("Registry did not report a digest for {}", image.whole())))
+    }
+}
@@ -0,0 +1,146 @@
+use std::sync::Arc;
+
+use axum::extract::{Path, State};
+use axum::http::{header, HeaderName, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, put};
+use axum::{Json, Router};
+use serde_json::json;
+
+use super::{ImageReference, ImageTag, RegistryMirror, DEFAULT_REGISTRY};
+use crate::error::ContainerError;
+
+const CONTENT_DIGEST: HeaderName = HeaderName::from_static("docker-content-digest");
+const API_VERSION: HeaderName = HeaderName::from_static("docker-distribution-api-version");
+
+#[derive(Debug, PartialEq, Eq)]
+enum PullPath {
+    Manifest(ImageReference),
+    Blob { registry: String, repository: String, digest: String },
+}
+
+// `<registry>/<repository>/manifests/<reference>` or `.../blobs/<digest>`. Without a known
+// registry up front the path is Docker Hub's, which is what dockerd sends to a mirror listed
+// in its `registry-mirrors`.
+fn parse_pull_path(path: &str, serves: impl Fn(&str) -> bool) -> Option<PullPath> {
+    let path = path.trim_start_matches('/');
+    let (name, manifest, reference) = match path.rsplit_once("/manifests/") {
+        Some((name, reference)) => (name, true, reference),
+        None => path.rsplit_once("/blobs/").map(|(name, digest)| (name, false, digest))?,
+    };
+    let (registry, repository) = match name.split_once('/') {
+        Some((registry, repository)) if serves(registry) => (registry, repository),
+        _ => (DEFAULT_REGISTRY, name),
+    };
+    if repository.is_empty() || reference.is_empty() {
+        return None;
+    }
+    let (registry, repository) = (registry.to_string(), repository.to_string());
+    Some(match manifest {
+        true => {
+            let reference = match reference.starts_with("sha256:") {
+                true => ImageTag::Digest(reference.to_string()),
+                false => ImageTag::Tag(reference.to_string()),
+            };
+            PullPath::Manifest(ImageReference { registry, repository, reference })
+        }
+        false => PullPath::Blob { registry, repository, digest: reference.to_string() },
+    })
+}
+
+// Errors in the shape registry clients expect
+fn registry_error(error: ContainerError, unknown: &str) -> Response {
+    let (status, code) = match &error {
+        ContainerError::NotFound(_) => (StatusCode::NOT_FOUND, unknown),
+        ContainerError::Validation(_) => (StatusCode::BAD_REQUEST, "NAME_INVALID"),
+        ContainerError::Permission(_) => (StatusCode::FORBIDDEN, "DENIED"),
+        _ => (StatusCode::BAD_GATEWAY, "UNKNOWN"),
+    };
+    (status, Json(json!({ "errors": [{ "code": code, "message": error.to_string() }] }))).into_response()
+}
+
+async fn version() -> Response {
+    ([(API_VERSION, "registry/2.0")], Json(json!({}))).into_response()
+}
+
+// HEAD is answered by the same handler with the body dropped
+async fn pull(State(mirror): State<Arc<RegistryMirror>>, Path(path): Path<String>) -> Response {
+    match parse_pull_path(&path, |registry| mirror.serves(registry)) {
+        Some(PullPath::Manifest(image)) => match mirror.manifest(&image).await {
+            Ok(manifest) => {
+                ([(header::CONTENT_TYPE, manifest.media_type), (CONTENT_DIGEST, manifest.digest)], manifest.body).into_response()
+            }
+            Err(e) => registry_error(e, "MANIFEST_UNKNOWN"),
+        },
+        Some(PullPath::Blob { registry, repository, digest }) => match mirror.blob(&registry, &repository, &digest).await {
+            Ok(data) => ([(header::CONTENT_TYPE, "application/octet-stream".to_string()), (CONTENT_DIGEST, digest)], data).into_response(),
+            Err(e) => registry_error(e, "BLOB_UNKNOWN"),
+        },
+        None => registry_error(ContainerError::NotFound(format!("Unknown registry path {}", path)), "UNSUPPORTED"),
+    }
+}
+
+// Read-only distribution API for runtimes to pull through
+pub fn pull_router(mirror: Arc<RegistryMirror>) -> Router {
+    Router::new().route("/v2/", get(version)).route("/v2/*path", get(pull)).with_state(mirror)
+}
+
+async fn stats(State(mirror): State<Arc<RegistryMirror>>) -> Response {
+    Json(mirror.stats().await).into_response()
+}
+
+async fn pins(State(mirror): State<Arc<RegistryMirror>>) -> Response {
+    Json(mirror.pins().await).into_response()
+}
+
+async fn pin(State(mirror): State<Arc<RegistryMirror>>, Path(image): Path<String>) -> Response {
+    match mirror.pin(image.trim_start_matches('/')).await {
+        Ok(pin) => Json(pin).into_response(),
+        Err(e) => registry_error(e, "MANIFEST_UNKNOWN"),
+    }
+}
+
+async fn unpin(State(mirror): State<Arc<RegistryMirror>>, Path(image): Path<String>) -> Response {
+    match mirror.unpin(image.trim_start_matches('/')).await {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => registry_error(e, "MANIFEST_UNKNOWN"),
+    }
+}
+
+// Stats and pins; kept apart from the pull routes so it can be served on an internal listener
+pub fn admin_router(mirror: Arc<RegistryMirror>) -> Router {
+    Router::new()
+        .route("/admin/mirror/stats", get(stats))
+        .route("/admin/mirror/pins", get(pins))
+        .route("/admin/mirror/pins/*image", put(pin).delete(unpin))
+        .with_state(mirror)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pull_paths() {
+        let serves = |registry: &str| ["docker.io", "ghcr.io"].contains(&registry);
+        let manifest = parse_pull_path("ghcr.io/sirsi/agent/manifests/1.4", serves).unwrap();
+        assert_eq!(manifest, PullPath::Manifest(ImageReference::parse("ghcr.io/sirsi/agent:1.4").unwrap()));
+
+        // What dockerd asks a Docker Hub mirror for
+        let hub = parse_pull_path("/library/nginx/manifests/sha256:abc", serves).unwrap();
+        assert_eq!(
+            hub,
+            PullPath::Manifest(ImageReference {
+                registry: "docker.io".into(),
+                repository: "library/nginx".into(),
+                reference: ImageTag::Digest("sha256:abc".into()),
+            })
+        );
+        assert_eq!(
+            parse_pull_path("docker.io/library/redis/blobs/sha256:def", serves),
+            Some(PullPath::Blob { registry: "docker.io".into(), repository: "library/redis".into(), digest: "sha256:def".into() })
+        );
+        assert_eq!(parse_pull_path("library/nginx/tags/list", serves), None);
+        assert_eq!(parse_pull_path("ghcr.io/manifests/", serves), None);
+    }
+}
@@ -0,0 +1,420 @@
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::process::Stdio;
+
+use async_trait::async_trait;
+use chrono::{DateTime, TimeZone, Utc};
+use serde_json::Value;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::sync::mpsc;
+use tracing::warn;
+
+use super::{
+    Container, ContainerConfig, ContainerRuntime, ContainerState, ContainerStats, ExecResult, Protocol, RuntimeEvent,
+    RuntimeEventKind,
+};
+use crate::error::{ContainerError, ContainerResult};
+use crate::registry::ImageReference;
+
+// Drives a local Docker daemon through the docker CLI
+pub struct DockerCliRuntime {
+    program: String,
+    mirror: Option<String>,
+}
+
+impl Default for DockerCliRuntime {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DockerCliRuntime {
+    pub fn new() -> Self {
+        Self { program: "docker".to_string(), mirror: None }
+    }
+
+    pub fn with_program(mut self, program: impl Into<String>) -> Self {
+        self.program = program.into();
+        self
+    }
+
+    // Registry mirror (`host:port`) images are pulled through before a container is created
+    pub fn with_mirror(mut self, mirror: impl Into<String>) -> Self {
+        self.mirror = Some(mirror.into());
+        self
+    }
+
+    // Tags what the mirror served under the original name, so containers don't show the
+    // mirror. If the mirror can't serve the image, the daemon pulls it from its own registry.
+    async fn pull_through(&self, mirror: &str, image: &str) {
+        let Ok(reference) = ImageReference::parse(image) else { return };
+        let mirrored = reference.mirrored(mirror);
+        let pulled = match self.run(&["pull".to_string(), "--quiet".to_string(), mirrored.clone()]).await {
+            Ok(_) => self.run(&["tag".to_string(), mirrored, image.to_string()]).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = pulled {
+            warn!("Pulling {} through mirror {} failed, falling back to its registry: {}", image, mirror, e);
+        }
+    }
+
+    async fn run(&self, args: &[String]) -> ContainerResult<String> {
+        let output = tokio::process::Command::new(&self.program)
+            .args(args)
+            .output()
+            .await
+            .map_err(|e| ContainerError::Platform(format!("Failed to run {}: {}", self.program, e)))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            if stderr.contains("No such container") || stderr.contains("No such object") {
+                return Err(ContainerError::NotFound(stderr));
+            }
+            return Err(ContainerError::Platform(format!(
+                "{} {} failed: {}",
+                self.program,
+                args.first().map(String::as_str).unwrap_or_default(),
+                stderr
+            )));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    fn create_args(config: &ContainerConfig) -> Vec<String> {
+        let mut args = vec!["create".to_string()];
+        for (key, value) in config.labels.iter().flatten() {
+            args.extend(["--label".to_string(), format!("{}={}", key, value)]);
+        }
+        for (key, value) in config.env.iter().flatten() {
+            args.extend(["--env".to_string(), format!("{}={}", key, value)]);
+        }
+        for port in config.ports.iter().flatten() {
+            let protocol = match port.protocol {
+                Protocol::TCP => "tcp",
+                Protocol::UDP => "udp",
+            };
+            // Without a host port Docker publishes on an ephemeral one
+            let mapping = match port.host_port {
+                Some(host) => format!("{}:{}/{}", host, port.container_port, protocol),
+                None => format!("{}/{}", port.container_port, protocol),
+            };
+            args.extend(["--publish".to_string(), mapping]);
+        }
+        for volume in config.volumes.iter().flatten() {
+            let mut mount = format!("{}:{}", volume.name, volume.mount_path);
+            if volume.read_only {
+                mount.push_str(":ro");
+            }
+            args.extend(["--volume".to_string(), mount]);
+        }
+        if let Some(resources) = &config.resources {
+            if let Some(cpu) = &resources.cpu {
+                args.extend(["--cpus".to_string(), cpu.clone()]);
+            }
+            if let Some(memory) = &resources.memory {
+                args.extend(["--memory".to_string(), memory.clone()]);
+            }
+            if let Some(gpu) = &resources.gpu {
+                args.extend(["--gpus".to_string(), gpu.clone()]);
+            }
+        }
+        let mut command = config.command.clone().unwrap_or_default().into_iter();
+        if let Some(entrypoint) = command.next() {
+            args.extend(["--entrypoint".to_string(), entrypoint]);
+        }
+        args.push(config.image.clone());
+        args.extend(command);
+        args.extend(config.args.clone().unwrap_or_default());
+        args
+    }
+}
+
+fn parse_time(value: &Value) -> Option<DateTime<Utc>> {
+    let time = DateTime::parse_from_rfc3339(value.as_str()?).ok()?.with_timezone(&Utc);
+    // Docker reports unset times as the zero value
+    (time.timestamp() > 0).then_some(time)
+}
+
+pub fn parse_inspect(value: &Value) -> ContainerResult<Container> {
+    let id = value["Id"]
+        .as_str()
+        .ok_or_else(|| ContainerError::Platform("docker inspect output has no Id".to_string()))?;
+    let state = &value["State"];
+    let status = match state["Status"].as_str().unwrap_or_default() {
+        "running" => ContainerState::Running,
+        "paused" => ContainerState::Paused,
+        "restarting" => ContainerState::Restarting,
+        "exited" => ContainerState::Exited,
+        "dead" => ContainerState::Dead,
+        _ => ContainerState::Created,
+    };
+    let labels = value["Config"]["Labels"]
+        .as_object()
+        .map(|labels| {
+            labels
+                .iter()
+                .filter_map(|(k, v)| Some((k.clone(), v.as_str()?.to_string())))
+                .collect()
+        })
+        .unwrap_or_default();
+    let exit_code = (!matches!(status, ContainerState::Created | ContainerState::Running))
+        .then(|| state["ExitCode"].as_i64().map(|c| c as i32))
+        .flatten();
+    Ok(Container {
+        id: id.to_string(),
+        name: value["Name"].as_str().unwrap_or_default().trim_start_matches('/').to_string(),
+        image: value["Config"]["Image"].as_str().unwrap_or_default().to_string(),
+        state: status,
+        created: parse_time(&value["Created"]).unwrap_or_else(Utc::now),
+        started: parse_time(&state["StartedAt"]),
+        finished: parse_time(&state["FinishedAt"]),
+        exit_code,
+        labels,
+    })
+}
+
+// Sizes as printed by `docker stats`, e.g. "12.5MiB" or "1.2kB"
+pub fn parse_size(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let split = value.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number.trim().parse().ok()?;
+    let multiplier: f64 = match unit {
+        "" | "B" => 1.0,
+        "kB" | "KB" => 1e3,
+        "MB" => 1e6,
+        "GB" => 1e9,
+        "TB" => 1e12,
+        "KiB" => 1024.0,
+        "MiB" => 1024.0 * 1024.0,
+        "GiB" => 1024.0 * 1024.0 * 1024.0,
+        "TiB" => 1024.0 * 1024.0 * 1024.0 * 1024.0,
+        _ => return None,
+    };
+    Some((number * multiplier).round() as u64)
+}
+
+fn parse_pair(value: &Value) -> (u64, u64) {
+    let mut parts = value.as_str().unwrap_or_default().split('/').map(|p| parse_size(p).unwrap_or(0));
+    (parts.next().unwrap_or(0), parts.next().unwrap_or(0))
+}
+
+pub fn parse_stats(line: &str) -> ContainerResult<ContainerStats> {
+    let value: Value = serde_json::from_str(line.trim())
+        .map_err(|e| ContainerError::Platform(format!("Invalid docker stats output: {}", e)))?;
+    // CPUPerc is a percentage of one core
+    let cpu_usage = value["CPUPerc"]
+        .as_str()
+        .and_then(|p| p.trim_end_matches('%').parse::<f64>().ok())
+        .map(|p| p / 100.0)
+        .unwrap_or(0.0);
+    let (memory_usage, memory_limit) = parse_pair(&value["MemUsage"]);
+    let (network_rx_bytes, network_tx_bytes) = parse_pair(&value["NetIO"]);
+    let (block_rx_bytes, block_tx_bytes) = parse_pair(&value["BlockIO"]);
+    Ok(ContainerStats {
+        cpu_usage,
+        memory_usage,
+        memory_limit,
+        network_rx_bytes,
+        network_tx_bytes,
+        block_rx_bytes,
+        block_tx_bytes,
+    })
+}
+
+pub fn parse_event(line: &str) -> Option<RuntimeEvent> {
+    let value: Value = serde_json::from_str(line.trim()).ok()?;
+    let kind = match value["Action"].as_str()? {
+        "start" => RuntimeEventKind::Start,
+        "oom" => RuntimeEventKind::Oom,
+        "restart" => RuntimeEventKind::Restart,
+        "die" => RuntimeEventKind::Die {
+            exit_code: value["Actor"]["Attributes"]["exitCode"]
+                .as_str()
+                .and_then(|c| c.parse().ok())
+                .unwrap_or(-1),
+        },
+        _ => return None,
+    };
+    let at = value["timeNano"]
+        .as_i64()
+        .map(|nanos| Utc.timestamp_nanos(nanos))
+        .unwrap_or_else(Utc::now);
+    Some(RuntimeEvent {
+        container_id: value["id"].as_str().or(value["Actor"]["ID"].as_str())?.to_string(),
+        kind,
+        at,
+    })
+}
+
+pub fn parse_port(output: &str) -> Option<SocketAddr> {
+    let address: SocketAddr = output.lines().find_map(|line| line.trim().parse().ok())?;
+    // A wildcard binding is reachable on loopback
+    if address.ip().is_unspecified() {
+        return Some(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), address.port()));
+    }
+    Some(address)
+}
+
+#[async_trait]
+impl ContainerRuntime for DockerCliRuntime {
+    async fn create_container(&self, config: ContainerConfig) -> ContainerResult<Container> {
+        if let Some(mirror) = &self.mirror {
+            self.pull_through(mirror, &config.image).await;
+        }
+        let id = self.run(&Self::create_args(&config)).await?;
+        self.get_container(id.trim()).await
+    }
+
+    async fn start_container(&self, id: &str) -> ContainerResult<()> {
+        self.run(&["start".to_string(), id.to_string()]).await.map(|_| ())
+    }
+
+    async fn stop_container(&self, id: &str) -> ContainerResult<()> {
+        self.run(&["stop".to_string(), id.to_string()]).await.map(|_| ())
+    }
+
+    async fn remove_container(&self, id: &str) -> ContainerResult<()> {
+        self.run(&["rm".to_string(), "--force".to_string(), id.to_string()]).await.map(|_| ())
+    }
+
+    async fn get_container(&self, id: &str) -> ContainerResult<Container> {
+        let output = self.run(&["inspect".to_string(), "--type".to_string(), "container".to_string(), id.to_string()]).await?;
+        let value: Value = serde_json::from_str(&output)
+            .map_err(|e| ContainerError::Platform(format!("Invalid docker inspect output: {}", e)))?;
+        let first = value
+            .as_array()
+            .and_then(|items| items.first())
+            .ok_or_else(|| ContainerError::NotFound(id.to_string()))?;
+        parse_inspect(first)
+    }
+
+    async fn list_containers(&self) -> ContainerResult<Vec<Container>> {
+        let ids = self.run(&["ps".to_string(), "--all".to_string(), "--quiet".to_string(), "--no-trunc".to_string()]).await?;
+        let mut containers = Vec::new();
+        for id in ids.lines().map(str::trim).filter(|id| !id.is_empty()) {
+            match self.get_container(id).await {
+                Ok(container) => containers.push(container),
+                // Removed between ps and inspect
+                Err(ContainerError::NotFound(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(containers)
+    }
+
+    async fn container_logs(&self, id: &str) -> ContainerResult<Vec<String>> {
+        let output = self.run(&["logs".to_string(), id.to_string()]).await?;
+        Ok(output.lines().map(str::to_string).collect())
+    }
+
+    async fn container_stats(&self, id: &str) -> ContainerResult<ContainerStats> {
+        let output = self
+            .run(&[
+                "stats".to_string(),
+                "--no-stream".to_string(),
+                "--format".to_string(),
+                "{{json .}}".to_string(),
+                id.to_string(),
+            ])
+            .await?;
+        parse_stats(&output)
+    }
+
+    async fn exec_in_container(&self, id: &str, cmd: Vec<String>) -> ContainerResult<ExecResult> {
+        let output = tokio::process::Command::new(&self.program)
+            .arg("exec")
+            .arg(id)
+            .args(&cmd)
+            .output()
+            .await
+            .map_err(|e| ContainerError::Platform(format!("Failed to run {}: {}", self.program, e)))?;
+        Ok(ExecResult {
+            exit_code: output.status.code().unwrap_or(-1),
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        })
+    }
+
+    async fn container_address(&self, id: &str, container_port: i32) -> ContainerResult<SocketAddr> {
+        let output = self.run(&["port".to_string(), id.to_string(), format!("{}/tcp", container_port)]).await?;
+        parse_port(&output)
+            .ok_or_else(|| ContainerError::NotFound(format!("Container {} does not publish port {}", id, container_port)))
+    }
+
+    async fn container_events(&self) -> ContainerResult<mpsc::Receiver<RuntimeEvent>> {
+        let mut child = tokio::process::Command::new(&self.program)
+            .args(["events", "--filter", "type=container", "--format", "{{json .}}"])
+            .stdout(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| ContainerError::Platform(format!("Failed to run {}: {}", self.program, e)))?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| ContainerError::Internal("docker events has no stdout".to_string()))?;
+        let (tx, rx) = mpsc::channel(256);
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stdout).lines();
+            loop {
+                match lines.next_line().await {
+                    Ok(Some(line)) => {
+                        if let Some(event) = parse_event(&line) {
+                            if tx.send(event).await.is_err() {
+                                break;
+                            }
+                        }
+                    }
+                    Ok(None) => break,
+                    Err(e) => {
+                        warn!("Failed to read docker events: {}", e);
+                        break;
+                    }
+                }
+            }
+            let _ = child.kill().await;
+        });
+        Ok(rx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_stats() {
+        let line = r#"{"BlockIO":"4.1MB / 0B","CPUPerc":"150.25%","Container":"abc","MemPerc":"0.63%","MemUsage":"12.5MiB / 1.944GiB","Name":"web","NetIO":"1.2kB / 648B","PIDs":"3"}"#;
+        let stats = parse_stats(line).unwrap();
+        assert!((stats.cpu_usage - 1.5025).abs() < 1e-9);
+        assert_eq!(stats.memory_usage, 13_107_200);
+        assert_eq!(stats.memory_limit, (1.944 * 1024.0 * 1024.0 * 1024.0_f64).round() as u64);
+        assert_eq!((stats.network_rx_bytes, stats.network_tx_bytes), (1200, 648));
+        assert_eq!((stats.block_rx_bytes, stats.block_tx_bytes), (4_100_000, 0));
+        assert_eq!(parse_size("7XB"), None);
+    }
+
+    #[test]
+    fn test_parse_inspect_and_events() {
+        let inspect = serde_json::json!({
+            "Id": "abc123",
+            "Name": "/web-1",
+            "Created": "2024-03-01T10:00:00.123456789Z",
+            "Config": {"Image": "nginx:1.25", "Labels": {"app": "web"}},
+            "State": {"Status": "exited", "ExitCode": 137, "StartedAt": "2024-03-01T10:00:01Z", "FinishedAt": "0001-01-01T00:00:00Z"}
+        });
+        let container = parse_inspect(&inspect).unwrap();
+        assert_eq!(container.name, "web-1");
+        assert!(matches!(container.state, ContainerState::Exited));
+        assert_eq!(container.exit_code, Some(137));
+        assert!(container.started.is_some());
+        assert!(container.finished.is_none());
+        assert_eq!(container.labels["app"], "web");
+
+        let die = r#"{"status":"die","id":"abc123","Type":"container","Action":"die","Actor":{"ID":"abc123","Attributes":{"exitCode":"137"}},"time":1709287200,"timeNano":1709287200000000000}"#;
+        let event = parse_event(die).unwrap();
+        assert_eq!(event.kind, RuntimeEventKind::Die { exit_code: 137 });
+        assert_eq!(event.at, Utc.with_ymd_and_hms(2024, 3, 1, 10, 0, 0).unwrap());
+        assert!(parse_event(r#"{"Action":"attach","id":"abc123"}"#).is_none());
+
+        assert_eq!(parse_port("0.0.0.0:49153\n[::]:49153\n"), Some("127.0.0.1:49153".parse().unwrap()));
+    }
+}
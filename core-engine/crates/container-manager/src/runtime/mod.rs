@@ -1,8 +1,14 @@
 use std::collections::HashMap;
+use std::net::SocketAddr;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
 
-use crate::error::{ContainerError, ContainerResult};
+use crate::error::ContainerResult;
+
+pub mod docker;
+
+pub use docker::DockerCliRuntime;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ContainerConfig {
@@ -77,6 +83,10 @@ pub trait ContainerRuntime: Send + Sync {
     async fn container_logs(&self, id: &str) -> ContainerResult<Vec<String>>;
     async fn container_stats(&self, id: &str) -> ContainerResult<ContainerStats>;
     async fn exec_in_container(&self, id: &str, cmd: Vec<String>) -> ContainerResult<ExecResult>;
+    // Where a published container port is reachable from this host
+    async fn container_address(&self, id: &str, container_port: i32) -> ContainerResult<SocketAddr>;
+    // Lifecycle events for all containers on this runtime, in the order the runtime reports them
+    async fn container_events(&self) -> ContainerResult<mpsc::Receiver<RuntimeEvent>>;
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -96,3 +106,18 @@ pub struct ExecResult {
     pub stdout: String,
     pub stderr: String,
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuntimeEvent {
+    pub container_id: String,
+    pub kind: RuntimeEventKind,
+    pub at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RuntimeEventKind {
+    Start,
+    Die { exit_code: i32 },
+    Oom,
+    Restart,
+}
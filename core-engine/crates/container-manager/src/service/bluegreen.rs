@@ -0,0 +1,829 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use super::{ContainerService, MANAGED_BY_LABEL, MANAGED_BY_VALUE};
+use crate::error::{ContainerError, ContainerResult};
+use crate::network::NetworkManager;
+use crate::runtime::{ContainerConfig, ContainerState};
+
+pub const SERVICE_LABEL: &str = "sirsi.io/service";
+pub const DEPLOYMENT_LABEL: &str = "sirsi.io/deployment";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ProbeCheck {
+    Http { path: String },
+    Tcp,
+    Exec { command: Vec<String> },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadinessProbe {
+    pub check: ProbeCheck,
+    pub interval: Duration,
+    pub attempt_timeout: Duration,
+    // How long a green container may take to become ready before the deploy fails
+    pub timeout: Duration,
+}
+
+impl ReadinessProbe {
+    pub fn http(path: impl Into<String>) -> Self {
+        Self {
+            check: ProbeCheck::Http { path: path.into() },
+            interval: Duration::from_secs(2),
+            attempt_timeout: Duration::from_secs(1),
+            timeout: Duration::from_secs(120),
+        }
+    }
+
+    pub fn tcp() -> Self {
+        Self {
+            check: ProbeCheck::Tcp,
+            ..Self::http("/")
+        }
+    }
+
+    pub fn exec(command: Vec<String>) -> Self {
+        Self {
+            check: ProbeCheck::Exec { command },
+            ..Self::http("/")
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServiceDefinition {
+    pub name: String,
+    pub replicas: usize,
+    // The container port that is probed and receives traffic
+    pub port: i32,
+    pub config: ContainerConfig,
+    pub readiness: ReadinessProbe,
+    pub bake_period: Duration,
+}
+
+impl ServiceDefinition {
+    pub fn validate(&self) -> ContainerResult<()> {
+        if self.name.is_empty() {
+            return Err(ContainerError::Validation("Service name is required".to_string()));
+        }
+        if self.replicas == 0 {
+            return Err(ContainerError::Validation(format!("Service {} needs at least one replica", self.name)));
+        }
+        if !(1..=65535).contains(&self.port) {
+            return Err(ContainerError::Validation(format!("Invalid service port {}", self.port)));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServiceTarget {
+    pub container_id: String,
+    pub address: SocketAddr,
+}
+
+// Moves a service's traffic from one set of containers to another. Implementations
+// must have the new set serving before the old set stops receiving requests.
+#[async_trait]
+pub trait TrafficSwitch: Send + Sync {
+    async fn switch(&self, service: &str, from: &[ServiceTarget], to: &[ServiceTarget]) -> ContainerResult<()>;
+}
+
+pub struct NetworkAttachmentSwitch {
+    network: Arc<dyn NetworkManager>,
+    network_id: String,
+}
+
+impl NetworkAttachmentSwitch {
+    pub fn new(network: Arc<dyn NetworkManager>, network_id: impl Into<String>) -> Self {
+        Self {
+            network,
+            network_id: network_id.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl TrafficSwitch for NetworkAttachmentSwitch {
+    async fn switch(&self, _service: &str, from: &[ServiceTarget], to: &[ServiceTarget]) -> ContainerResult<()> {
+        for target in to {
+            self.network.connect_container(&self.network_id, &target.container_id).await?;
+        }
+        for target in from.iter().filter(|t| !to.contains(t)) {
+            self.network.disconnect_container(&self.network_id, &target.container_id).await?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DeploymentPhase {
+    CreatingGreen,
+    AwaitingReadiness,
+    Switched,
+    Completed,
+    RolledBack,
+    Aborted,
+    Failed,
+}
+
+impl DeploymentPhase {
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            DeploymentPhase::Completed | DeploymentPhase::RolledBack | DeploymentPhase::Aborted | DeploymentPhase::Failed
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PhaseTransition {
+    pub phase: DeploymentPhase,
+    pub at: DateTime<Utc>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeploymentRecord {
+    pub id: String,
+    pub service: String,
+    pub image: String,
+    pub definition: ServiceDefinition,
+    pub phase: DeploymentPhase,
+    pub blue: Vec<ServiceTarget>,
+    pub green: Vec<String>,
+    pub green_targets: Vec<ServiceTarget>,
+    pub bake_until: Option<DateTime<Utc>>,
+    pub error: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub transitions: Vec<PhaseTransition>,
+}
+
+impl DeploymentRecord {
+    fn transition(&mut self, phase: DeploymentPhase, message: impl Into<String>) {
+        let now = Utc::now();
+        let message = message.into();
+        info!("Deployment {} of {}: {:?} {}", self.id, self.service, phase, message);
+        self.phase = phase;
+        self.updated_at = now;
+        self.transitions.push(PhaseTransition { phase, at: now, message });
+    }
+}
+
+// Persistence for blue/green deployments so an interrupted deploy can be resumed
+#[async_trait]
+pub trait DeploymentStore: Send + Sync {
+    async fn save_deployment(&self, record: &DeploymentRecord) -> ContainerResult<()>;
+    async fn get_deployment(&self, id: &str) -> ContainerResult<Option<DeploymentRecord>>;
+    async fn list_deployments(&self, service: &str) -> ContainerResult<Vec<DeploymentRecord>>;
+    async fn list_active_deployments(&self) -> ContainerResult<Vec<DeploymentRecord>>;
+}
+
+#[derive(Default)]
+pub struct InMemoryDeploymentStore {
+    deployments: RwLock<HashMap<String, DeploymentRecord>>,
+}
+
+impl InMemoryDeploymentStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl DeploymentStore for InMemoryDeploymentStore {
+    async fn save_deployment(&self, record: &DeploymentRecord) -> ContainerResult<()> {
+        self.deployments.write().await.insert(record.id.clone(), record.clone());
+        Ok(())
+    }
+
+    async fn get_deployment(&self, id: &str) -> ContainerResult<Option<DeploymentRecord>> {
+        Ok(self.deployments.read().await.get(id).cloned())
+    }
+
+    async fn list_deployments(&self, service: &str) -> ContainerResult<Vec<DeploymentRecord>> {
+        let mut records: Vec<DeploymentRecord> = self
+            .deployments
+            .read()
+            .await
+            .values()
+            .filter(|r| r.service == service)
+            .cloned()
+            .collect();
+        records.sort_by_key(|r| r.started_at);
+        Ok(records)
+    }
+
+    async fn list_active_deployments(&self) -> ContainerResult<Vec<DeploymentRecord>> {
+        let mut records: Vec<DeploymentRecord> = self
+            .deployments
+            .read()
+            .await
+            .values()
+            .filter(|r| !r.phase.is_terminal())
+            .cloned()
+            .collect();
+        records.sort_by_key(|r| r.started_at);
+        Ok(records)
+    }
+}
+
+async fn http_ready(address: SocketAddr, path: &str) -> std::io::Result<bool> {
+    let mut stream = TcpStream::connect(address).await?;
+    let request = format!("GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n", path, address);
+    stream.write_all(request.as_bytes()).await?;
+    let mut head = [0u8; 32];
+    let read = stream.read(&mut head).await?;
+    let status = std::str::from_utf8(&head[..read])
+        .ok()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|code| code.parse::<u16>().ok());
+    Ok(status.is_some_and(|code| (200..400).contains(&code)))
+}
+
+impl ContainerService {
+    fn traffic_switch(&self) -> ContainerResult<&Arc<dyn TrafficSwitch>> {
+        self.traffic
+            .as_ref()
+            .ok_or_else(|| ContainerError::Config("No traffic switch configured for blue/green deployments".to_string()))
+    }
+
+    async fn service_targets(&self, definition: &ServiceDefinition, exclude: &str) -> ContainerResult<Vec<ServiceTarget>> {
+        let mut targets = Vec::new();
+        for container in self.runtime.list_containers().await? {
+            let labels = &container.labels;
+            if labels.get(SERVICE_LABEL) != Some(&definition.name)
+                || labels.get(DEPLOYMENT_LABEL).map(String::as_str) == Some(exclude)
+                || !matches!(container.state, ContainerState::Running)
+            {
+                continue;
+            }
+            let address = self.runtime.container_address(&container.id, definition.port).await?;
+            targets.push(ServiceTarget { container_id: container.id, address });
+        }
+        targets.sort_by(|a, b| a.container_id.cmp(&b.container_id));
+        Ok(targets)
+    }
+
+    fn green_config(record: &DeploymentRecord) -> ContainerConfig {
+        let mut config = record.definition.config.clone();
+        config.image = record.image.clone();
+        let labels = config.labels.get_or_insert_with(HashMap::new);
+        labels.insert(MANAGED_BY_LABEL.to_string(), MANAGED_BY_VALUE.to_string());
+        labels.insert(SERVICE_LABEL.to_string(), record.service.clone());
+        labels.insert(DEPLOYMENT_LABEL.to_string(), record.id.clone());
+        config
+    }
+
+    async fn probe_once(&self, container_id: &str, address: SocketAddr, probe: &ReadinessProbe) -> bool {
+        let attempt = async {
+            match &probe.check {
+                ProbeCheck::Http { path } => http_ready(address, path).await.unwrap_or(false),
+                ProbeCheck::Tcp => TcpStream::connect(address).await.is_ok(),
+                ProbeCheck::Exec { command } => self
+                    .runtime
+                    .exec_in_container(container_id, command.clone())
+                    .await
+                    .is_ok_and(|result| result.exit_code == 0),
+            }
+        };
+        tokio::time::timeout(probe.attempt_timeout, attempt).await.unwrap_or(false)
+    }
+
+    async fn wait_ready(&self, target: &ServiceTarget, probe: &ReadinessProbe) -> bool {
+        let deadline = tokio::time::Instant::now() + probe.timeout;
+        loop {
+            if self.probe_once(&target.container_id, target.address, probe).await {
+                return true;
+            }
+            if tokio::time::Instant::now() + probe.interval > deadline {
+                return false;
+            }
+            tokio::time::sleep(probe.interval).await;
+        }
+    }
+
+    async fn remove_containers(&self, ids: &[String]) {
+        for id in ids {
+            match self.runtime.remove_container(id).await {
+                Ok(()) | Err(ContainerError::NotFound(_)) => {}
+                Err(e) => warn!("Failed to remove container {}: {}", id, e),
+            }
+        }
+    }
+
+    // Green containers include any created before a crash prevented the record from being saved
+    async fn green_containers(&self, record: &DeploymentRecord) -> ContainerResult<Vec<String>> {
+        let mut green = record.green.clone();
+        for container in self.runtime.list_containers().await? {
+            if container.labels.get(DEPLOYMENT_LABEL) == Some(&record.id) && !green.contains(&container.id) {
+                green.push(container.id);
+            }
+        }
+        Ok(green)
+    }
+
+    // Records the failure and returns the error the deploy should surface
+    async fn fail_deployment(&self, record: &mut DeploymentRecord, error: String) -> ContainerError {
+        let green = match self.green_containers(record).await {
+            Ok(green) => green,
+            Err(e) => return e,
+        };
+        self.remove_containers(&green).await;
+        record.error = Some(error.clone());
+        record.transition(DeploymentPhase::Failed, error.clone());
+        match self.deployments.save_deployment(record).await {
+            Ok(()) => ContainerError::Deployment(error),
+            Err(e) => e,
+        }
+    }
+
+    async fn drive_deployment(&self, mut record: DeploymentRecord) -> ContainerResult<DeploymentRecord> {
+        loop {
+            match record.phase {
+                DeploymentPhase::CreatingGreen => {
+                    record.green = self.green_containers(&record).await?;
+                    while record.green.len() < record.definition.replicas {
+                        let container = self.runtime.create_container(Self::green_config(&record)).await?;
+                        record.green.push(container.id);
+                        self.deployments.save_deployment(&record).await?;
+                    }
+                    for id in &record.green {
+                        let container = self.runtime.get_container(id).await?;
+                        if !matches!(container.state, ContainerState::Running) {
+                            self.runtime.start_container(id).await?;
+                        }
+                    }
+                    record.transition(
+                        DeploymentPhase::AwaitingReadiness,
+                        format!("Started {} green containers", record.green.len()),
+                    );
+                    self.deployments.save_deployment(&record).await?;
+                }
+                DeploymentPhase::AwaitingReadiness => {
+                    let mut targets = Vec::new();
+                    for id in &record.green {
+                        let address = self.runtime.container_address(id, record.definition.port).await?;
+                        targets.push(ServiceTarget { container_id: id.clone(), address });
+                    }
+                    for target in &targets {
+                        if !self.wait_ready(target, &record.definition.readiness).await {
+                            let error = format!(
+                                "Container {} did not become ready within {}s",
+                                target.container_id,
+                                record.definition.readiness.timeout.as_secs()
+                            );
+                            return Err(self.fail_deployment(&mut record, error).await);
+                        }
+                    }
+                    record.green_targets = targets;
+                    if let Err(e) = self
+                        .traffic_switch()?
+                        .switch(&record.service, &record.blue, &record.green_targets)
+                        .await
+                    {
+                        // Blue never stopped serving, so dropping green restores the old state
+                        return Err(self.fail_deployment(&mut record, format!("Traffic switch failed: {}", e)).await);
+                    }
+                    let bake = chrono::Duration::from_std(record.definition.bake_period).unwrap_or_default();
+                    record.bake_until = Some(Utc::now() + bake);
+                    record.transition(DeploymentPhase::Switched, "Traffic switched to green");
+                    self.deployments.save_deployment(&record).await?;
+                }
+                DeploymentPhase::Switched
+                | DeploymentPhase::Completed
+                | DeploymentPhase::RolledBack
+                | DeploymentPhase::Aborted
+                | DeploymentPhase::Failed => return Ok(record),
+            }
+        }
+    }
+
+    // Creates green alongside the running containers and switches traffic once every green
+    // container passes its readiness probe; blue is kept until the bake period ends
+    pub async fn deploy_blue_green(&self, definition: ServiceDefinition, image: &str) -> ContainerResult<DeploymentRecord> {
+        definition.validate()?;
+        self.traffic_switch()?;
+        if let Some(active) = self
+            .deployments
+            .list_deployments(&definition.name)
+            .await?
+            .into_iter()
+            .find(|r| !r.phase.is_terminal())
+        {
+            return Err(ContainerError::Deployment(format!(
+                "Service {} already has deployment {} in phase {:?}",
+                definition.name, active.id, active.phase
+            )));
+        }
+
+        let id = uuid::Uuid::new_v4().to_string();
+        let blue = self.service_targets(&definition, &id).await?;
+        let now = Utc::now();
+        let mut record = DeploymentRecord {
+            id,
+            service: definition.name.clone(),
+            image: image.to_string(),
+            definition,
+            phase: DeploymentPhase::CreatingGreen,
+            blue,
+            green: Vec::new(),
+            green_targets: Vec::new(),
+            bake_until: None,
+            error: None,
+            started_at: now,
+            updated_at: now,
+            transitions: Vec::new(),
+        };
+        record.transition(DeploymentPhase::CreatingGreen, format!("Deploying {}", image));
+        self.deployments.save_deployment(&record).await?;
+        self.drive_deployment(record).await
+    }
+
+    pub async fn resume_deployment(&self, deployment_id: &str) -> ContainerResult<DeploymentRecord> {
+        let record = self.require_deployment(deployment_id).await?;
+        self.drive_deployment(record).await
+    }
+
+    // Picks up every deploy that was interrupted before its traffic switch
+    pub async fn resume_interrupted_deployments(&self) -> Vec<ContainerResult<DeploymentRecord>> {
+        let active = match self.deployments.list_active_deployments().await {
+            Ok(active) => active,
+            Err(e) => return vec![Err(e)],
+        };
+        let mut results = Vec::new();
+        for record in active.into_iter().filter(|r| r.phase != DeploymentPhase::Switched) {
+            results.push(self.drive_deployment(record).await);
+        }
+        results
+    }
+
+    pub async fn abort_deployment(&self, deployment_id: &str) -> ContainerResult<DeploymentRecord> {
+        let mut record = self.require_deployment(deployment_id).await?;
+        match record.phase {
+            DeploymentPhase::CreatingGreen | DeploymentPhase::AwaitingReadiness => {
+                let green = self.green_containers(&record).await?;
+                self.remove_containers(&green).await;
+                record.transition(DeploymentPhase::Aborted, "Aborted before traffic switch");
+                self.deployments.save_deployment(&record).await?;
+                Ok(record)
+            }
+            DeploymentPhase::Switched => self.roll_back_record(record).await,
+            phase => Err(ContainerError::Deployment(format!(
+                "Deployment {} already finished in phase {:?}",
+                deployment_id, phase
+            ))),
+        }
+    }
+
+    // Sends traffic back to blue while it is still baking and removes green
+    pub async fn rollback(&self, service: &str) -> ContainerResult<DeploymentRecord> {
+        let record = self
+            .deployments
+            .list_deployments(service)
+            .await?
+            .into_iter()
+            .find(|r| r.phase == DeploymentPhase::Switched)
+            .ok_or_else(|| ContainerError::NotFound(format!("No deployment of {} is baking", service)))?;
+        self.roll_back_record(record).await
+    }
+
+    async fn roll_back_record(&self, mut record: DeploymentRecord) -> ContainerResult<DeploymentRecord> {
+        self.traffic_switch()?
+            .switch(&record.service, &record.green_targets, &record.blue)
+            .await?;
+        self.remove_containers(&record.green).await;
+        record.transition(DeploymentPhase::RolledBack, "Traffic returned to blue");
+        self.deployments.save_deployment(&record).await?;
+        Ok(record)
+    }
+
+    // Tears blue down for every deployment whose bake period has ended
+    pub async fn complete_baked_deployments(&self, now: DateTime<Utc>) -> ContainerResult<Vec<DeploymentRecord>> {
+        let mut completed = Vec::new();
+        for mut record in self.deployments.list_active_deployments().await? {
+            if record.phase != DeploymentPhase::Switched || record.bake_until.is_some_and(|until| until > now) {
+                continue;
+            }
+            let blue: Vec<String> = record.blue.iter().map(|t| t.container_id.clone()).collect();
+            self.remove_containers(&blue).await;
+            record.transition(DeploymentPhase::Completed, format!("Removed {} blue containers", blue.len()));
+            self.deployments.save_deployment(&record).await?;
+            completed.push(record);
+        }
+        Ok(completed)
+    }
+
+    pub fn spawn_bake_reaper(self: Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = self.complete_baked_deployments(Utc::now()).await {
+                    warn!("Failed to complete baked deployments: {}", e);
+                }
+            }
+        })
+    }
+
+    async fn require_deployment(&self, deployment_id: &str) -> ContainerResult<DeploymentRecord> {
+        self.deployments
+            .get_deployment(deployment_id)
+            .await?
+            .ok_or_else(|| ContainerError::NotFound(format!("Deployment {} not found", deployment_id)))
+    }
+
+    pub async fn get_deployment(&self, deployment_id: &str) -> ContainerResult<DeploymentRecord> {
+        self.require_deployment(deployment_id).await
+    }
+
+    pub async fn deployment_history(&self, service: &str) -> ContainerResult<Vec<DeploymentRecord>> {
+        self.deployments.list_deployments(service).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::tests::MockRuntime;
+    use std::sync::atomic::Ordering;
+
+    type Switches = std::sync::Mutex<Vec<(Vec<String>, Vec<String>)>>;
+
+    #[derive(Default)]
+    struct RecordingSwitch {
+        switches: Switches,
+    }
+
+    impl RecordingSwitch {
+        fn switches(&self) -> Vec<(Vec<String>, Vec<String>)> {
+            self.switches.lock().unwrap().clone()
+        }
+    }
+
+    #[async_trait]
+    impl TrafficSwitch for RecordingSwitch {
+        async fn switch(&self, _service: &str, from: &[ServiceTarget], to: &[ServiceTarget]) -> ContainerResult<()> {
+            let ids = |targets: &[ServiceTarget]| targets.iter().map(|t| t.container_id.clone()).collect();
+            self.switches.lock().unwrap().push((ids(from), ids(to)));
+            Ok(())
+        }
+    }
+
+    fn definition() -> ServiceDefinition {
+        ServiceDefinition {
+            name: "web".to_string(),
+            replicas: 2,
+            port: 8080,
+            config: ContainerConfig {
+                image: "web:1".to_string(),
+                command: None,
+                args: None,
+                env: None,
+                ports: None,
+                volumes: None,
+                resources: None,
+                labels: None,
+            },
+            readiness: ReadinessProbe::exec(vec!["true".to_string()])
+                .with_interval(Duration::from_millis(10))
+                .with_timeout(Duration::from_millis(50)),
+            bake_period: Duration::from_secs(600),
+        }
+    }
+
+    fn setup() -> (Arc<MockRuntime>, Arc<RecordingSwitch>, Arc<InMemoryDeploymentStore>, ContainerService) {
+        let runtime = Arc::new(MockRuntime::default());
+        for id in ["blue-0", "blue-1"] {
+            runtime.add_with_labels(id, HashMap::from([(SERVICE_LABEL.to_string(), "web".to_string())]));
+        }
+        let switch = Arc::new(RecordingSwitch::default());
+        let store = Arc::new(InMemoryDeploymentStore::new());
+        let service = ContainerService::new(runtime.clone())
+            .with_traffic_switch(switch.clone())
+            .with_deployment_store(store.clone());
+        (runtime, switch, store, service)
+    }
+
+    fn strings(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_deploy_bake_and_rollback() {
+        let (runtime, switch, _, service) = setup();
+
+        let first = service.deploy_blue_green(definition(), "web:2").await.unwrap();
+        assert_eq!(first.phase, DeploymentPhase::Switched);
+        assert_eq!(first.green, strings(&["green-0", "green-1"]));
+        assert_eq!(switch.switches(), vec![(strings(&["blue-0", "blue-1"]), first.green.clone())]);
+        // Blue stays up while green bakes
+        assert_eq!(runtime.ids().len(), 4);
+
+        assert!(service.complete_baked_deployments(Utc::now()).await.unwrap().is_empty());
+        let later = Utc::now() + chrono::Duration::minutes(11);
+        let completed = service.complete_baked_deployments(later).await.unwrap();
+        assert_eq!(completed.len(), 1);
+        assert_eq!(completed[0].phase, DeploymentPhase::Completed);
+        assert_eq!(runtime.ids(), first.green);
+
+        let second = service.deploy_blue_green(definition(), "web:3").await.unwrap();
+        assert_eq!(second.blue.iter().map(|t| t.container_id.clone()).collect::<Vec<_>>(), first.green);
+        let rolled_back = service.rollback("web").await.unwrap();
+        assert_eq!(rolled_back.phase, DeploymentPhase::RolledBack);
+        assert_eq!(switch.switches().last().unwrap(), &(second.green.clone(), first.green.clone()));
+        assert_eq!(runtime.ids(), first.green);
+
+        let history = service.deployment_history("web").await.unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(service.get_deployment(&second.id).await.unwrap().phase, DeploymentPhase::RolledBack);
+        assert!(matches!(service.rollback("web").await, Err(ContainerError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_failed_readiness_keeps_blue() {
+        let (runtime, switch, _, service) = setup();
+
+        let result = service.deploy_blue_green(definition(), "web:broken").await;
+        assert!(matches!(result, Err(ContainerError::Deployment(_))));
+        let history = service.deployment_history("web").await.unwrap();
+        assert_eq!(history[0].phase, DeploymentPhase::Failed);
+        assert!(history[0].error.as_deref().unwrap().contains("did not become ready"));
+        assert_eq!(runtime.ids(), strings(&["blue-0", "blue-1"]));
+        assert!(switch.switches().is_empty());
+
+        service.deploy_blue_green(definition(), "web:2").await.unwrap();
+        assert!(matches!(
+            service.deploy_blue_green(definition(), "web:3").await,
+            Err(ContainerError::Deployment(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_resume_after_interruption() {
+        let (runtime, switch, store, service) = setup();
+
+        runtime.fail_next_start.store(true, Ordering::SeqCst);
+        assert!(service.deploy_blue_green(definition(), "web:2").await.is_err());
+        let interrupted = service.deployment_history("web").await.unwrap().remove(0);
+        assert_eq!(interrupted.phase, DeploymentPhase::CreatingGreen);
+        assert_eq!(interrupted.green.len(), 2);
+
+        // A new process with the same store finishes the deploy without recreating green
+        let restarted = ContainerService::new(runtime.clone())
+            .with_traffic_switch(switch.clone())
+            .with_deployment_store(store.clone());
+        let resumed = restarted.resume_interrupted_deployments().await;
+        assert_eq!(resumed.len(), 1);
+        let resumed = resumed.into_iter().next().unwrap().unwrap();
+        assert_eq!(resumed.phase, DeploymentPhase::Switched);
+        assert_eq!(resumed.green, interrupted.green);
+        assert_eq!(runtime.ids().len(), 4);
+
+        restarted.complete_baked_deployments(Utc::now() + chrono::Duration::hours(1)).await.unwrap();
+        runtime.fail_next_start.store(true, Ordering::SeqCst);
+        assert!(restarted.deploy_blue_green(definition(), "web:3").await.is_err());
+        let pending = restarted.deployment_history("web").await.unwrap().pop().unwrap();
+        let aborted = restarted.abort_deployment(&pending.id).await.unwrap();
+        assert_eq!(aborted.phase, DeploymentPhase::Aborted);
+        assert_eq!(runtime.ids(), resumed.green);
+    }
+
+    #[cfg(feature = "docker-tests")]
+    mod docker {
+        use super::*;
+        use crate::runtime::{DockerCliRuntime, PortMapping, Protocol};
+        use std::sync::atomic::{AtomicBool, AtomicUsize};
+        use tokio::net::TcpListener;
+
+        // Round-robins connections over whatever the last switch pointed at
+        #[derive(Default)]
+        struct ProxySwitch {
+            upstreams: std::sync::RwLock<Vec<SocketAddr>>,
+            next: AtomicUsize,
+        }
+
+        impl ProxySwitch {
+            fn pick(&self) -> Option<SocketAddr> {
+                let upstreams = self.upstreams.read().unwrap();
+                (!upstreams.is_empty()).then(|| upstreams[self.next.fetch_add(1, Ordering::Relaxed) % upstreams.len()])
+            }
+        }
+
+        #[async_trait]
+        impl TrafficSwitch for ProxySwitch {
+            async fn switch(&self, _service: &str, _from: &[ServiceTarget], to: &[ServiceTarget]) -> ContainerResult<()> {
+                *self.upstreams.write().unwrap() = to.iter().map(|t| t.address).collect();
+                Ok(())
+            }
+        }
+
+        async fn serve(listener: TcpListener, switch: Arc<ProxySwitch>) {
+            loop {
+                let Ok((mut client, _)) = listener.accept().await else { return };
+                let Some(upstream) = switch.pick() else { continue };
+                tokio::spawn(async move {
+                    if let Ok(mut server) = TcpStream::connect(upstream).await {
+                        let _ = tokio::io::copy_bidirectional(&mut client, &mut server).await;
+                    }
+                });
+            }
+        }
+
+        fn nginx(image: &str) -> ServiceDefinition {
+            ServiceDefinition {
+                name: format!("bluegreen-test-{}", uuid::Uuid::new_v4()),
+                replicas: 2,
+                port: 80,
+                config: ContainerConfig {
+                    image: image.to_string(),
+                    command: None,
+                    args: None,
+                    env: None,
+                    ports: Some(vec![PortMapping {
+                        container_port: 80,
+                        host_port: None,
+                        protocol: Protocol::TCP,
+                    }]),
+                    volumes: None,
+                    resources: None,
+                    labels: None,
+                },
+                readiness: ReadinessProbe::http("/").with_interval(Duration::from_millis(200)),
+                bake_period: Duration::from_secs(60),
+            }
+        }
+
+        #[tokio::test]
+        async fn test_switch_without_failed_requests() {
+            let switch = Arc::new(ProxySwitch::default());
+            let service = ContainerService::new(Arc::new(DockerCliRuntime::new())).with_traffic_switch(switch.clone());
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let proxy = listener.local_addr().unwrap();
+            tokio::spawn(serve(listener, switch.clone()));
+
+            let blue_definition = nginx("nginx:1.25-alpine");
+            let blue = service.deploy_blue_green(blue_definition.clone(), "nginx:1.25-alpine").await.unwrap();
+            service
+                .complete_baked_deployments(Utc::now() + chrono::Duration::hours(1))
+                .await
+                .unwrap();
+
+            let done = Arc::new(AtomicBool::new(false));
+            let load = {
+                let done = done.clone();
+                tokio::spawn(async move {
+                    let (mut ok, mut failed) = (0u64, 0u64);
+                    while !done.load(Ordering::SeqCst) {
+                        match http_ready(proxy, "/").await {
+                            Ok(true) => ok += 1,
+                            _ => failed += 1,
+                        }
+                    }
+                    (ok, failed)
+                })
+            };
+
+            let mut green_definition = blue_definition.clone();
+            green_definition.config.image = "nginx:1.27-alpine".to_string();
+            let green = service.deploy_blue_green(green_definition, "nginx:1.27-alpine").await;
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            done.store(true, Ordering::SeqCst);
+            let (ok, failed) = load.await.unwrap();
+
+            let green = green.unwrap();
+            service
+                .complete_baked_deployments(Utc::now() + chrono::Duration::hours(1))
+                .await
+                .unwrap();
+            service.remove_containers(&green.green).await;
+            service.remove_containers(&blue.green).await;
+
+            assert_eq!(green.phase, DeploymentPhase::Switched);
+            assert!(ok > 0);
+            assert_eq!(failed, 0);
+        }
+    }
+}
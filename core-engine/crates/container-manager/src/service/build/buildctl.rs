@@ -0,0 +1,290 @@
+use std::collections::HashMap;
+use std::process::Stdio;
+
+use async_trait::async_trait;
+use base64::Engine;
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::sync::mpsc;
+
+use super::{BuildBackend, ImageDescriptor, SolveEntry, SolveLog, SolveRequest, SolveResult};
+use crate::error::{ContainerError, ContainerResult};
+
+// Drives a BuildKit daemon through `buildctl`. Progress is read as raw JSON from stderr and
+// the pushed manifest from the metadata file buildctl writes when the solve ends.
+pub struct BuildctlBackend {
+    program: String,
+    addr: Option<String>,
+    git: String,
+}
+
+impl Default for BuildctlBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Vertex {
+    digest: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    cached: bool,
+    completed: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct VertexLog {
+    vertex: String,
+    #[serde(default)]
+    data: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct SolveStatus {
+    #[serde(default)]
+    vertexes: Vec<Vertex>,
+    #[serde(default)]
+    logs: Vec<VertexLog>,
+}
+
+// Turns `--progress rawjson` updates into log lines and keeps count of cached steps
+#[derive(Default)]
+pub struct ProgressParser {
+    names: HashMap<String, String>,
+    // Completed vertices and whether the cache answered them
+    completed: HashMap<String, bool>,
+}
+
+impl ProgressParser {
+    // `None` for anything that is not a status update, such as buildctl's own errors
+    pub fn parse(&mut self, line: &str) -> Option<Vec<SolveLog>> {
+        let status: SolveStatus = serde_json::from_str(line).ok()?;
+        let mut logs = Vec::new();
+        for vertex in status.vertexes {
+            if !vertex.name.is_empty() {
+                self.names.insert(vertex.digest.clone(), vertex.name.clone());
+            }
+            let name = self.names.get(&vertex.digest).cloned().unwrap_or_default();
+            if let Some(error) = vertex.error {
+                logs.push(SolveLog { vertex: name.clone(), line: format!("ERROR: {}", error) });
+            }
+            if vertex.completed.is_some() && !self.completed.contains_key(&vertex.digest) {
+                self.completed.insert(vertex.digest, vertex.cached);
+                logs.push(SolveLog { line: format!("{} {}", if vertex.cached { "CACHED" } else { "DONE" }, name), vertex: name });
+            }
+        }
+        for log in status.logs {
+            let name = self.names.get(&log.vertex).cloned().unwrap_or_default();
+            let data = base64::engine::general_purpose::STANDARD.decode(&log.data).unwrap_or_default();
+            for line in String::from_utf8_lossy(&data).lines().filter(|l| !l.trim().is_empty()) {
+                logs.push(SolveLog { vertex: name.clone(), line: line.to_string() });
+            }
+        }
+        Some(logs)
+    }
+
+    // (hits, misses)
+    pub fn cache_counts(&self) -> (u64, u64) {
+        let hits = self.completed.values().filter(|cached| **cached).count() as u64;
+        (hits, self.completed.len() as u64 - hits)
+    }
+}
+
+fn entry_arg(entry: &SolveEntry) -> String {
+    let mut arg = format!("type={}", entry.kind);
+    for (key, value) in &entry.attrs {
+        arg.push_str(&format!(",{}={}", key, value));
+    }
+    arg
+}
+
+pub fn build_args(request: &SolveRequest, metadata_file: &str) -> Vec<String> {
+    let mut args: Vec<String> = ["build", "--progress", "rawjson", "--frontend", &request.frontend].map(String::from).to_vec();
+    for (key, value) in &request.frontend_attrs {
+        args.extend(["--opt".to_string(), format!("{}={}", key, value)]);
+    }
+    for (name, dir) in &request.local_dirs {
+        args.extend(["--local".to_string(), format!("{}={}", name, dir.display())]);
+    }
+    for (flag, entries) in [("--output", &request.exports), ("--import-cache", &request.cache_imports), ("--export-cache", &request.cache_exports)] {
+        for entry in entries {
+            args.extend([flag.to_string(), entry_arg(entry)]);
+        }
+    }
+    args.extend(["--metadata-file".to_string(), metadata_file.to_string()]);
+    args
+}
+
+fn parse_metadata(metadata: &str) -> ContainerResult<ImageDescriptor> {
+    let metadata: Value = serde_json::from_str(metadata)
+        .map_err(|e| ContainerError::Platform(format!("Unexpected buildctl metadata: {}", e)))?;
+    let descriptor = &metadata["containerimage.descriptor"];
+    match (descriptor["mediaType"].as_str(), descriptor["digest"].as_str(), descriptor["size"].as_u64()) {
+        (Some(media_type), Some(digest), Some(size)) => {
+            Ok(ImageDescriptor { media_type: media_type.to_string(), digest: digest.to_string(), size })
+        }
+        _ => Err(ContainerError::Platform("buildctl metadata has no image descriptor".to_string())),
+    }
+}
+
+fn is_commit(reference: &str) -> bool {
+    reference.len() == 40 && reference.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+impl BuildctlBackend {
+    pub fn new() -> Self {
+        Self { program: "buildctl".to_string(), addr: None, git: "git".to_string() }
+    }
+
+    pub fn with_program(mut self, program: impl Into<String>) -> Self {
+        self.program = program.into();
+        self
+    }
+
+    // e.g. `tcp://buildkitd:1234`; buildctl's own default socket otherwise
+    pub fn with_addr(mut self, addr: impl Into<String>) -> Self {
+        self.addr = Some(addr.into());
+        self
+    }
+
+    fn command(&self) -> tokio::process::Command {
+        let mut command = tokio::process::Command::new(&self.program);
+        if let Some(addr) = &self.addr {
+            command.args(["--addr", addr]);
+        }
+        command.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped());
+        command
+    }
+
+    async fn output(&self, mut command: tokio::process::Command, program: &str) -> ContainerResult<String> {
+        let output = command
+            .output()
+            .await
+            .map_err(|e| ContainerError::Platform(format!("Failed to run {}: {}", program, e)))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            return Err(ContainerError::Platform(format!("{} failed: {}", program, stderr)));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+#[async_trait]
+impl BuildBackend for BuildctlBackend {
+    async fn version(&self) -> ContainerResult<String> {
+        let mut command = self.command();
+        command.args(["debug", "info"]);
+        let output = self.output(command, &self.program).await?;
+        // `BuildKit: github.com/moby/buildkit v0.13.2 <revision>`
+        let version = output
+            .lines()
+            .find_map(|line| line.strip_prefix("BuildKit:"))
+            .and_then(|line| line.split_whitespace().find(|word| word.starts_with('v')))
+            .ok_or_else(|| ContainerError::Platform(format!("Unexpected buildctl debug info: {}", output.trim())))?;
+        Ok(format!("buildkit {}", version))
+    }
+
+    async fn resolve_git(&self, url: &str, reference: &str) -> ContainerResult<String> {
+        if is_commit(reference) {
+            return Ok(reference.to_lowercase());
+        }
+        let mut command = tokio::process::Command::new(&self.git);
+        command.args(["ls-remote", "--", url, reference]).stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped());
+        let output = self.output(command, &self.git).await?;
+        let refs: Vec<(&str, &str)> = output.lines().filter_map(|line| line.split_once('\t')).collect();
+        // An annotated tag's peeled entry names the commit rather than the tag object
+        refs.iter()
+            .find(|(_, name)| name.ends_with("^{}"))
+            .or_else(|| refs.first())
+            .map(|(commit, _)| commit.to_string())
+            .ok_or_else(|| ContainerError::NotFound(format!("{} has no branch or tag {}", url, reference)))
+    }
+
+    async fn solve(&self, request: &SolveRequest, logs: mpsc::Sender<SolveLog>) -> ContainerResult<SolveResult> {
+        let metadata = tempfile::NamedTempFile::new()
+            .map_err(|e| ContainerError::Internal(format!("Failed to create metadata file: {}", e)))?;
+        let mut command = self.command();
+        command.args(build_args(request, &metadata.path().display().to_string()));
+        let mut child = command
+            .spawn()
+            .map_err(|e| ContainerError::Platform(format!("Failed to run {}: {}", self.program, e)))?;
+        let stderr = child
+            .stderr
+            .take()
+            .ok_or_else(|| ContainerError::Internal("buildctl stderr was not captured".to_string()))?;
+
+        let mut parser = ProgressParser::default();
+        let mut errors = Vec::new();
+        let mut lines = BufReader::new(stderr).lines();
+        while let Some(line) = lines
+            .next_line()
+            .await
+            .map_err(|e| ContainerError::Platform(format!("Failed to read buildctl progress: {}", e)))?
+        {
+            match parser.parse(&line) {
+                Some(parsed) => {
+                    for log in parsed {
+                        // Whoever was listening went away; the build carries on regardless
+                        let _ = logs.send(log).await;
+                    }
+                }
+                None if !line.trim().is_empty() => errors.push(line),
+                None => {}
+            }
+        }
+        let status = child
+            .wait()
+            .await
+            .map_err(|e| ContainerError::Platform(format!("Failed to run {}: {}", self.program, e)))?;
+        if !status.success() {
+            return Err(ContainerError::Platform(format!("buildctl build failed: {}", errors.join("\n"))));
+        }
+        let metadata = tokio::fs::read_to_string(metadata.path())
+            .await
+            .map_err(|e| ContainerError::Platform(format!("Failed to read buildctl metadata: {}", e)))?;
+        let (cache_hits, cache_misses) = parser.cache_counts();
+        Ok(SolveResult { manifest: parse_metadata(&metadata)?, cache_hits, cache_misses })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_progress_and_arguments() {
+        let mut parser = ProgressParser::default();
+        let started = r#"{"vertexes":[{"digest":"sha256:a","name":"[1/2] FROM docker.io/library/rust:1.75"},{"digest":"sha256:b","name":"[2/2] RUN cargo build"}]}"#;
+        assert_eq!(parser.parse(started).unwrap(), Vec::new());
+        // "Compiling api\n"
+        let running = r#"{"vertexes":[{"digest":"sha256:a","cached":true,"completed":"2024-05-01T10:00:01Z"}],"logs":[{"vertex":"sha256:b","stream":1,"data":"Q29tcGlsaW5nIGFwaQo="}]}"#;
+        let logs = parser.parse(running).unwrap();
+        assert_eq!(logs[0].line, "CACHED [1/2] FROM docker.io/library/rust:1.75");
+        assert_eq!(logs[1], SolveLog { vertex: "[2/2] RUN cargo build".into(), line: "Compiling api".into() });
+        parser.parse(r#"{"vertexes":[{"digest":"sha256:b","completed":"2024-05-01T10:00:09Z"}]}"#).unwrap();
+        assert_eq!(parser.cache_counts(), (1, 1));
+        assert!(parser.parse("error: failed to solve: failed to read dockerfile").is_none());
+
+        let request = SolveRequest {
+            frontend: "dockerfile.v0".into(),
+            frontend_attrs: BTreeMap::from([("platform".into(), "linux/arm64".into())]),
+            local_dirs: BTreeMap::from([("context".into(), "/tmp/ctx".into())]),
+            exports: vec![SolveEntry::new("image", &[("name", "registry.sirsi.local/acme/api"), ("push", "true")])],
+            cache_imports: Vec::new(),
+            cache_exports: Vec::new(),
+        };
+        let args = build_args(&request, "/tmp/meta.json").join(" ");
+        assert_eq!(
+            args,
+            "build --progress rawjson --frontend dockerfile.v0 --opt platform=linux/arm64 --local context=/tmp/ctx \
+             --output type=image,name=registry.sirsi.local/acme/api,push=true --metadata-file /tmp/meta.json"
+        );
+        let metadata = r#"{"containerimage.descriptor":{"mediaType":"application/vnd.oci.image.manifest.v1+json","digest":"sha256:c","size":812}}"#;
+        assert_eq!(parse_metadata(metadata).unwrap().size, 812);
+    }
+}
@@ -0,0 +1,21 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use flate2::read::GzDecoder;
+
+use crate::error::{ContainerError, ContainerResult};
+
+// Unpacks an uploaded `.tar.gz` build context. `tar` refuses entries that would land outside
+// `dir`, so a crafted archive cannot write anywhere else on the builder host.
+pub async fn unpack_context(tarball: &Path, dir: &Path) -> ContainerResult<()> {
+    let (tarball, dir): (PathBuf, PathBuf) = (tarball.to_path_buf(), dir.to_path_buf());
+    tokio::task::spawn_blocking(move || {
+        let file = File::open(&tarball)
+            .map_err(|e| ContainerError::Validation(format!("Cannot open build context {}: {}", tarball.display(), e)))?;
+        tar::Archive::new(GzDecoder::new(file))
+            .unpack(&dir)
+            .map_err(|e| ContainerError::Validation(format!("Build context {} is not a valid .tar.gz: {}", tarball.display(), e)))
+    })
+    .await
+    .map_err(|e| ContainerError::Internal(format!("Context unpacking panicked: {}", e)))?
+}
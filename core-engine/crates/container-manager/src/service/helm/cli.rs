@@ -0,0 +1,207 @@
+use std::io::Write;
+use std::process::Stdio;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde_json::Value;
+use tokio::io::AsyncWriteExt;
+
+use super::{ChartRepository, HelmBackend, HelmRelease, ReleaseTarget};
+use crate::error::{ContainerError, ContainerResult};
+
+// Drives helm 3 through its CLI. Values go over stdin and the kubeconfig through a private
+// temporary file, so neither shows up in the process list.
+pub struct HelmCli {
+    program: String,
+}
+
+impl Default for HelmCli {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HelmCli {
+    pub fn new() -> Self {
+        Self { program: "helm".to_string() }
+    }
+
+    pub fn with_program(mut self, program: impl Into<String>) -> Self {
+        self.program = program.into();
+        self
+    }
+
+    async fn run(&self, args: &[String], kubeconfig: Option<&str>, stdin: Option<String>) -> ContainerResult<String> {
+        let mut args = args.to_vec();
+        // Held until helm exits; dropping it deletes the file
+        let kubeconfig_file = match kubeconfig {
+            Some(kubeconfig) => {
+                let mut file = tempfile::NamedTempFile::new()
+                    .map_err(|e| ContainerError::Internal(format!("Failed to create kubeconfig file: {}", e)))?;
+                file.write_all(kubeconfig.as_bytes())
+                    .map_err(|e| ContainerError::Internal(format!("Failed to write kubeconfig file: {}", e)))?;
+                args.extend(["--kubeconfig".to_string(), file.path().display().to_string()]);
+                Some(file)
+            }
+            None => None,
+        };
+        let mut child = tokio::process::Command::new(&self.program)
+            .args(&args)
+            .stdin(if stdin.is_some() { Stdio::piped() } else { Stdio::null() })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| ContainerError::Platform(format!("Failed to run {}: {}", self.program, e)))?;
+        if let (Some(input), Some(mut pipe)) = (stdin, child.stdin.take()) {
+            pipe.write_all(input.as_bytes())
+                .await
+                .map_err(|e| ContainerError::Platform(format!("Failed to pass values to {}: {}", self.program, e)))?;
+        }
+        let output = child
+            .wait_with_output()
+            .await
+            .map_err(|e| ContainerError::Platform(format!("Failed to run {}: {}", self.program, e)))?;
+        drop(kubeconfig_file);
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            if stderr.contains("not found") {
+                return Err(ContainerError::NotFound(stderr));
+            }
+            return Err(ContainerError::Platform(format!(
+                "{} {} failed: {}",
+                self.program,
+                args.first().map(String::as_str).unwrap_or_default(),
+                stderr
+            )));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    fn timeout(timeout: Duration) -> String {
+        format!("{}s", timeout.as_secs().max(1))
+    }
+}
+
+pub fn parse_release(output: &str) -> ContainerResult<HelmRelease> {
+    let release: Value = serde_json::from_str(output)
+        .map_err(|e| ContainerError::Platform(format!("Unexpected helm output: {}", e)))?;
+    let revision = release["version"]
+        .as_u64()
+        .ok_or_else(|| ContainerError::Platform("helm output has no release version".to_string()))?;
+    Ok(HelmRelease {
+        revision: revision as u32,
+        manifest: release["manifest"].as_str().unwrap_or_default().to_string(),
+        notes: release["info"]["notes"].as_str().filter(|n| !n.is_empty()).map(str::to_string),
+    })
+}
+
+#[async_trait]
+impl HelmBackend for HelmCli {
+    async fn add_repository(&self, repository: &ChartRepository) -> ContainerResult<()> {
+        let args = ["repo", "add", &repository.name, &repository.url, "--force-update"].map(String::from);
+        self.run(&args, None, None).await.map(|_| ())
+    }
+
+    async fn update_repositories(&self) -> ContainerResult<()> {
+        self.run(&["repo".to_string(), "update".to_string()], None, None).await.map(|_| ())
+    }
+
+    // Workload readiness is left to the deployer's health checks; hooks still have to pass
+    async fn upgrade_install(
+        &self,
+        target: &ReleaseTarget,
+        chart: &str,
+        version: &str,
+        values: &Value,
+        timeout: Duration,
+    ) -> ContainerResult<HelmRelease> {
+        let mut args: Vec<String> = ["upgrade", "--install", &target.name, chart, "--namespace", &target.namespace, "--create-namespace"]
+            .map(String::from)
+            .to_vec();
+        if !version.is_empty() {
+            args.extend(["--version".to_string(), version.to_string()]);
+        }
+        args.extend(["--reset-values", "--values", "-", "--output", "json", "--timeout"].map(String::from));
+        args.push(Self::timeout(timeout));
+        let output = self.run(&args, Some(&target.kubeconfig), Some(values.to_string())).await?;
+        parse_release(&output)
+    }
+
+    async fn rollback(&self, target: &ReleaseTarget, revision: u32, timeout: Duration) -> ContainerResult<HelmRelease> {
+        let revision = revision.to_string();
+        let timeout = Self::timeout(timeout);
+        let args = ["rollback", &target.name, &revision, "--namespace", &target.namespace, "--wait", "--timeout", &timeout].map(String::from);
+        self.run(&args, Some(&target.kubeconfig), None).await?;
+        let args = ["status", &target.name, "--namespace", &target.namespace, "--output", "json"].map(String::from);
+        parse_release(&self.run(&args, Some(&target.kubeconfig), None).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_release_output() {
+        let output = r#"{"name":"web","info":{"status":"deployed","notes":""},"manifest":"---\nkind: Service\n","version":4,"namespace":"shop"}"#;
+        let release = parse_release(output).unwrap();
+        assert_eq!(release.revision, 4);
+        assert_eq!(release.manifest, "---\nkind: Service\n");
+        assert_eq!(release.notes, None);
+        assert!(parse_release("Error: no deployed releases").is_err());
+    }
+
+    // Needs helm, kind and a cluster: KIND_CLUSTER names it (default "kind"). Uses the chart
+    // `helm create` scaffolds, which runs nginx.
+    #[cfg(feature = "kind-tests")]
+    mod kind {
+        use std::collections::HashMap;
+        use std::sync::Arc;
+
+        use super::*;
+        use crate::platform::KubeClusterAccess;
+        use crate::service::helm::{HelmDeployer, ReleaseStatus, ValueOverrides};
+
+        #[tokio::test]
+        async fn test_install_upgrade_and_rollback_on_kind() {
+            let cluster = std::env::var("KIND_CLUSTER").unwrap_or_else(|_| "kind".to_string());
+            let kubeconfig = tokio::process::Command::new("kind")
+                .args(["get", "kubeconfig", "--name", &cluster])
+                .output()
+                .await
+                .expect("kind is installed");
+            assert!(kubeconfig.status.success(), "kind cluster {} is running", cluster);
+            let charts = tempfile::tempdir().unwrap();
+            let status = tokio::process::Command::new("helm")
+                .args(["create", "web"])
+                .current_dir(charts.path())
+                .status()
+                .await
+                .unwrap();
+            assert!(status.success());
+            let chart = charts.path().join("web").display().to_string();
+
+            let kubeconfigs = HashMap::from([(cluster.clone(), String::from_utf8(kubeconfig.stdout).unwrap())]);
+            let deployer = HelmDeployer::new(Arc::new(HelmCli::new()), Arc::new(kubeconfigs))
+                .with_health(Arc::new(KubeClusterAccess::new()))
+                .with_timeout(Duration::from_secs(90))
+                .with_poll_interval(Duration::from_secs(2));
+            let namespace = format!("helm-test-{}", &uuid::Uuid::new_v4().simple().to_string()[..8]);
+            let values = ValueOverrides::new().with_set("replicaCount", 1);
+
+            let installed = deployer.install_release(&cluster, &namespace, "web", &chart, "", &values).await.unwrap();
+            assert_eq!(installed.status, ReleaseStatus::Deployed);
+            assert!(installed.manifest.contains("kind: Deployment"));
+
+            // An image that can never pull leaves the deployment unavailable until the deadline
+            let broken = values.clone().with_set("image.repository", "registry.invalid/none").with_set("image.tag", "missing");
+            let err = deployer.upgrade_release(&cluster, &namespace, "web", None, Some(&broken)).await.unwrap_err();
+            assert!(err.to_string().contains("rolled back to revision 1"));
+
+            let history = deployer.release_history(&cluster, &namespace, "web").await.unwrap();
+            assert_eq!(history.last().unwrap().status, ReleaseStatus::Deployed);
+            let diff = deployer.diff_revisions(&cluster, &namespace, "web", 1, 2).await.unwrap();
+            assert!(diff.values.iter().any(|c| c.path == "image.repository"));
+        }
+    }
+}
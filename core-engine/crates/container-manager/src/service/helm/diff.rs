@@ -0,0 +1,156 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::ReleaseRevision;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ValueChange {
+    pub path: String,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResourceChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceChange {
+    // `Kind/name` of the rendered object, or its template source when it has no name
+    pub resource: String,
+    pub change: ResourceChangeKind,
+    // Changed lines, prefixed with `-` or `+`
+    pub lines: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReleaseDiff {
+    pub from: u32,
+    pub to: u32,
+    pub chart: Option<(String, String)>,
+    pub values: Vec<ValueChange>,
+    pub resources: Vec<ResourceChange>,
+}
+
+impl ReleaseDiff {
+    pub fn between(from: &ReleaseRevision, to: &ReleaseRevision) -> Self {
+        let chart = (from.chart != to.chart || from.version != to.version)
+            .then(|| (format!("{}@{}", from.chart, from.version), format!("{}@{}", to.chart, to.version)));
+        Self {
+            from: from.revision,
+            to: to.revision,
+            chart,
+            values: values_diff(&from.values, &to.values),
+            resources: manifest_diff(&from.manifest, &to.manifest),
+        }
+    }
+}
+
+fn flatten(value: &Value, prefix: String, out: &mut BTreeMap<String, Value>) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, value) in map {
+                let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+                flatten(value, path, out);
+            }
+        }
+        value => {
+            out.insert(prefix, value.clone());
+        }
+    }
+}
+
+pub fn values_diff(before: &Value, after: &Value) -> Vec<ValueChange> {
+    let (mut old, mut new) = (BTreeMap::new(), BTreeMap::new());
+    flatten(before, String::new(), &mut old);
+    flatten(after, String::new(), &mut new);
+    let mut paths: Vec<&String> = old.keys().chain(new.keys()).collect();
+    paths.sort();
+    paths.dedup();
+    paths
+        .into_iter()
+        .filter(|path| old.get(*path) != new.get(*path))
+        .map(|path| ValueChange { path: path.clone(), before: old.get(path).cloned(), after: new.get(path).cloned() })
+        .collect()
+}
+
+// Splits a rendered manifest into its YAML documents, keyed by `Kind/name`
+fn documents(manifest: &str) -> BTreeMap<String, Vec<&str>> {
+    let mut documents = BTreeMap::new();
+    for document in manifest.split("\n---") {
+        let lines: Vec<&str> = document.lines().filter(|l| !l.trim().is_empty() && l.trim() != "---").collect();
+        if lines.iter().all(|l| l.trim_start().starts_with('#')) {
+            continue;
+        }
+        let field = |name: &str| {
+            lines.iter().find_map(|l| l.strip_prefix(name).map(|v| v.trim().trim_matches('"').to_string()))
+        };
+        let source = lines.iter().find_map(|l| l.strip_prefix("# Source: ")).unwrap_or_default();
+        let key = match (field("kind:"), field("  name:")) {
+            (Some(kind), Some(name)) => format!("{}/{}", kind, name),
+            _ => source.to_string(),
+        };
+        documents.insert(key, lines);
+    }
+    documents
+}
+
+// Longest-common-subsequence line diff; manifests are small enough that O(n*m) is fine
+fn line_diff(before: &[&str], after: &[&str]) -> Vec<String> {
+    let (n, m) = (before.len(), after.len());
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if before[i] == after[j] { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+        }
+    }
+    let (mut i, mut j, mut lines) = (0, 0, Vec::new());
+    while i < n || j < m {
+        if i < n && j < m && before[i] == after[j] {
+            i += 1;
+            j += 1;
+        } else if i < n && (j == m || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            lines.push(format!("-{}", before[i]));
+            i += 1;
+        } else {
+            lines.push(format!("+{}", after[j]));
+            j += 1;
+        }
+    }
+    lines
+}
+
+pub fn manifest_diff(before: &str, after: &str) -> Vec<ResourceChange> {
+    let (old, new) = (documents(before), documents(after));
+    let mut changes = Vec::new();
+    for (resource, lines) in &old {
+        match new.get(resource) {
+            None => changes.push(ResourceChange {
+                resource: resource.clone(),
+                change: ResourceChangeKind::Removed,
+                lines: lines.iter().map(|l| format!("-{}", l)).collect(),
+            }),
+            Some(updated) if updated != lines => changes.push(ResourceChange {
+                resource: resource.clone(),
+                change: ResourceChangeKind::Changed,
+                lines: line_diff(lines, updated),
+            }),
+            Some(_) => {}
+        }
+    }
+    for (resource, lines) in new.iter().filter(|(r, _)| !old.contains_key(*r)) {
+        changes.push(ResourceChange {
+            resource: resource.clone(),
+            change: ResourceChangeKind::Added,
+            lines: lines.iter().map(|l| format!("+{}", l)).collect(),
+        });
+    }
+    changes.sort_by(|a, b| a.resource.cmp(&b.resource));
+    changes
+}
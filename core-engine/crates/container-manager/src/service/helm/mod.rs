@@ -0,0 +1,567 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sirsi_key_vault::secret::SecretManager;
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+use crate::error::{ContainerError, ContainerResult};
+use crate::platform::cluster::KubeconfigSource;
+
+pub mod cli;
+pub mod diff;
+pub mod values;
+
+pub use cli::HelmCli;
+pub use diff::{ReleaseDiff, ResourceChange, ResourceChangeKind, ValueChange};
+pub use values::ValueOverrides;
+
+use values::{resolve_secrets, Redaction};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChartRepository {
+    pub name: String,
+    pub url: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReleaseStatus {
+    Deployed,
+    Superseded,
+    Failed,
+}
+
+// One revision of a release as SirsiNexus saw it. `values` keeps vault references unresolved
+// and `manifest` has resolved secrets masked, so neither holds plaintext.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReleaseRevision {
+    pub cluster: String,
+    pub namespace: String,
+    pub name: String,
+    pub revision: u32,
+    pub chart: String,
+    pub version: String,
+    pub values: Value,
+    pub manifest: String,
+    pub notes: Option<String>,
+    pub status: ReleaseStatus,
+    pub description: String,
+    pub deployed_at: DateTime<Utc>,
+}
+
+// Where a helm command runs
+pub struct ReleaseTarget {
+    pub kubeconfig: String,
+    pub namespace: String,
+    pub name: String,
+}
+
+// What helm reports back for the revision it just wrote
+#[derive(Debug, Clone, PartialEq)]
+pub struct HelmRelease {
+    pub revision: u32,
+    pub manifest: String,
+    pub notes: Option<String>,
+}
+
+// The helm operations the deployer needs. Implementations render and apply the chart
+// themselves, running its hooks and failing if any of them fail.
+#[async_trait]
+pub trait HelmBackend: Send + Sync {
+    async fn add_repository(&self, repository: &ChartRepository) -> ContainerResult<()>;
+    async fn update_repositories(&self) -> ContainerResult<()>;
+    async fn upgrade_install(
+        &self,
+        target: &ReleaseTarget,
+        chart: &str,
+        version: &str,
+        values: &Value,
+        timeout: Duration,
+    ) -> ContainerResult<HelmRelease>;
+    async fn rollback(&self, target: &ReleaseTarget, revision: u32, timeout: Duration) -> ContainerResult<HelmRelease>;
+}
+
+// Reports a release's workloads that are not yet serving, as `Kind/name`
+#[async_trait]
+pub trait ReleaseHealth: Send + Sync {
+    async fn unhealthy_workloads(&self, kubeconfig: &str, namespace: &str, release: &str) -> ContainerResult<Vec<String>>;
+}
+
+#[async_trait]
+pub trait ReleaseStore: Send + Sync {
+    async fn save_revision(&self, revision: &ReleaseRevision) -> ContainerResult<()>;
+    // Oldest first
+    async fn list_revisions(&self, cluster: &str, namespace: &str, name: &str) -> ContainerResult<Vec<ReleaseRevision>>;
+    // The latest revision of every release, optionally on one cluster
+    async fn list_releases(&self, cluster: Option<&str>) -> ContainerResult<Vec<ReleaseRevision>>;
+}
+
+#[derive(Default)]
+pub struct InMemoryReleaseStore {
+    releases: RwLock<HashMap<(String, String, String), Vec<ReleaseRevision>>>,
+}
+
+impl InMemoryReleaseStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl ReleaseStore for InMemoryReleaseStore {
+    async fn save_revision(&self, revision: &ReleaseRevision) -> ContainerResult<()> {
+        let key = (revision.cluster.clone(), revision.namespace.clone(), revision.name.clone());
+        let mut releases = self.releases.write().await;
+        let revisions = releases.entry(key).or_default();
+        revisions.retain(|r| r.revision != revision.revision);
+        revisions.push(revision.clone());
+        revisions.sort_by_key(|r| r.revision);
+        Ok(())
+    }
+
+    async fn list_revisions(&self, cluster: &str, namespace: &str, name: &str) -> ContainerResult<Vec<ReleaseRevision>> {
+        let key = (cluster.to_string(), namespace.to_string(), name.to_string());
+        Ok(self.releases.read().await.get(&key).cloned().unwrap_or_default())
+    }
+
+    async fn list_releases(&self, cluster: Option<&str>) -> ContainerResult<Vec<ReleaseRevision>> {
+        let mut releases: Vec<ReleaseRevision> = self
+            .releases
+            .read()
+            .await
+            .iter()
+            .filter(|((c, _, _), _)| cluster.is_none_or(|cluster| c == cluster))
+            .filter_map(|(_, revisions)| revisions.last().cloned())
+            .collect();
+        releases.sort_by(|a, b| (&a.cluster, &a.namespace, &a.name).cmp(&(&b.cluster, &b.namespace, &b.name)));
+        Ok(releases)
+    }
+}
+
+fn validate_name(kind: &str, name: &str) -> ContainerResult<()> {
+    let valid = !name.is_empty()
+        && name.len() <= 53
+        && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        && !name.starts_with('-')
+        && !name.ends_with('-');
+    if !valid {
+        return Err(ContainerError::Validation(format!(
+            "{} name '{}' must be up to 53 lowercase letters, digits or dashes",
+            kind, name
+        )));
+    }
+    Ok(())
+}
+
+// Installs and upgrades Helm releases on managed clusters, keeping a revision history that
+// can be diffed and rolling an upgrade back when its hooks fail or its workloads stay unhealthy
+pub struct HelmDeployer {
+    backend: Arc<dyn HelmBackend>,
+    kubeconfigs: Arc<dyn KubeconfigSource>,
+    store: Arc<dyn ReleaseStore>,
+    secrets: Option<Arc<dyn SecretManager>>,
+    health: Option<Arc<dyn ReleaseHealth>>,
+    repositories: RwLock<HashMap<String, ChartRepository>>,
+    timeout: Duration,
+    poll_interval: Duration,
+}
+
+impl HelmDeployer {
+    pub fn new(backend: Arc<dyn HelmBackend>, kubeconfigs: Arc<dyn KubeconfigSource>) -> Self {
+        Self {
+            backend,
+            kubeconfigs,
+            store: Arc::new(InMemoryReleaseStore::new()),
+            secrets: None,
+            health: None,
+            repositories: RwLock::new(HashMap::new()),
+            timeout: Duration::from_secs(300),
+            poll_interval: Duration::from_secs(5),
+        }
+    }
+
+    pub fn with_store(mut self, store: Arc<dyn ReleaseStore>) -> Self {
+        self.store = store;
+        self
+    }
+
+    // Resolves `vault:` references in release values
+    pub fn with_secrets(mut self, secrets: Arc<dyn SecretManager>) -> Self {
+        self.secrets = Some(secrets);
+        self
+    }
+
+    pub fn with_health(mut self, health: Arc<dyn ReleaseHealth>) -> Self {
+        self.health = Some(health);
+        self
+    }
+
+    // How long hooks may run, and then how long workloads may take to become healthy
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    pub async fn add_repository(&self, repository: ChartRepository) -> ContainerResult<()> {
+        validate_name("Repository", &repository.name)?;
+        if !["https://", "http://", "oci://"].iter().any(|scheme| repository.url.starts_with(scheme)) {
+            return Err(ContainerError::Validation(format!("Unsupported chart repository URL {}", repository.url)));
+        }
+        self.backend.add_repository(&repository).await?;
+        info!(repository = %repository.name, url = %repository.url, "Added chart repository");
+        self.repositories.write().await.insert(repository.name.clone(), repository);
+        Ok(())
+    }
+
+    pub async fn update_repositories(&self) -> ContainerResult<()> {
+        self.backend.update_repositories().await
+    }
+
+    pub async fn repositories(&self) -> Vec<ChartRepository> {
+        let mut repositories: Vec<ChartRepository> = self.repositories.read().await.values().cloned().collect();
+        repositories.sort_by(|a, b| a.name.cmp(&b.name));
+        repositories
+    }
+
+    pub async fn install_release(
+        &self,
+        cluster: &str,
+        namespace: &str,
+        name: &str,
+        chart: &str,
+        version: &str,
+        values: &ValueOverrides,
+    ) -> ContainerResult<ReleaseRevision> {
+        validate_name("Release", name)?;
+        validate_name("Namespace", namespace)?;
+        let revisions = self.store.list_revisions(cluster, namespace, name).await?;
+        if revisions.iter().any(|r| r.status == ReleaseStatus::Deployed) {
+            return Err(ContainerError::Validation(format!("Release {} already exists in {}/{}; upgrade it instead", name, cluster, namespace)));
+        }
+        let latest = revisions.last().map_or(0, |r| r.revision);
+        self.deploy(cluster, namespace, name, chart, version, values.merged()?, latest, None).await
+    }
+
+    // Upgrades to a new chart version and/or values; `None` keeps what the deployed revision used
+    pub async fn upgrade_release(
+        &self,
+        cluster: &str,
+        namespace: &str,
+        name: &str,
+        version: Option<&str>,
+        values: Option<&ValueOverrides>,
+    ) -> ContainerResult<ReleaseRevision> {
+        let revisions = self.store.list_revisions(cluster, namespace, name).await?;
+        let current = revisions
+            .iter()
+            .rev()
+            .find(|r| r.status == ReleaseStatus::Deployed)
+            .cloned()
+            .ok_or_else(|| ContainerError::NotFound(format!("No deployed release {} in {}/{}", name, cluster, namespace)))?;
+        let values = match values {
+            Some(values) => values.merged()?,
+            None => current.values.clone(),
+        };
+        let version = version.unwrap_or(&current.version).to_string();
+        let latest = revisions.last().map_or(0, |r| r.revision);
+        let chart = current.chart.clone();
+        self.deploy(cluster, namespace, name, &chart, &version, values, latest, Some(current)).await
+    }
+
+    pub async fn list_releases(&self, cluster: Option<&str>) -> ContainerResult<Vec<ReleaseRevision>> {
+        self.store.list_releases(cluster).await
+    }
+
+    pub async fn release_history(&self, cluster: &str, namespace: &str, name: &str) -> ContainerResult<Vec<ReleaseRevision>> {
+        self.store.list_revisions(cluster, namespace, name).await
+    }
+
+    pub async fn diff_revisions(&self, cluster: &str, namespace: &str, name: &str, from: u32, to: u32) -> ContainerResult<ReleaseDiff> {
+        let revisions = self.store.list_revisions(cluster, namespace, name).await?;
+        let find = |revision: u32| {
+            revisions
+                .iter()
+                .find(|r| r.revision == revision)
+                .ok_or_else(|| ContainerError::NotFound(format!("Release {} has no revision {}", name, revision)))
+        };
+        Ok(ReleaseDiff::between(find(from)?, find(to)?))
+    }
+
+    async fn wait_healthy(&self, target: &ReleaseTarget) -> ContainerResult<()> {
+        let Some(health) = &self.health else { return Ok(()) };
+        let deadline = tokio::time::Instant::now() + self.timeout;
+        loop {
+            let unhealthy = health.unhealthy_workloads(&target.kubeconfig, &target.namespace, &target.name).await?;
+            if unhealthy.is_empty() {
+                return Ok(());
+            }
+            if tokio::time::Instant::now() >= deadline {
+                return Err(ContainerError::Deployment(format!(
+                    "workloads still unhealthy after {:?}: {}",
+                    self.timeout,
+                    unhealthy.join(", ")
+                )));
+            }
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn deploy(
+        &self,
+        cluster: &str,
+        namespace: &str,
+        name: &str,
+        chart: &str,
+        version: &str,
+        values: Value,
+        latest: u32,
+        previous: Option<ReleaseRevision>,
+    ) -> ContainerResult<ReleaseRevision> {
+        let target = ReleaseTarget {
+            kubeconfig: self.kubeconfigs.kubeconfig(cluster).await?,
+            namespace: namespace.to_string(),
+            name: name.to_string(),
+        };
+        let (rendered, redactions) = resolve_secrets(&values, self.secrets.as_deref()).await?;
+        let redact = |text: &str| Redaction::apply(&redactions, text);
+        let mut revision = ReleaseRevision {
+            cluster: cluster.to_string(),
+            namespace: namespace.to_string(),
+            name: name.to_string(),
+            revision: latest + 1,
+            chart: chart.to_string(),
+            version: version.to_string(),
+            values,
+            manifest: String::new(),
+            notes: None,
+            status: ReleaseStatus::Deployed,
+            description: if previous.is_some() { "Upgrade complete" } else { "Install complete" }.to_string(),
+            deployed_at: Utc::now(),
+        };
+
+        let outcome = match self.backend.upgrade_install(&target, chart, version, &rendered, self.timeout).await {
+            Ok(release) => {
+                revision.revision = release.revision;
+                revision.manifest = redact(&release.manifest);
+                revision.notes = release.notes.as_deref().map(redact);
+                self.wait_healthy(&target).await
+            }
+            Err(e) => Err(e),
+        };
+        // Errors can quote rendered values back at us
+        let failure = outcome.err().map(|e| redact(&e.to_string()));
+
+        let Some(reason) = failure else {
+            if let Some(mut previous) = previous {
+                previous.status = ReleaseStatus::Superseded;
+                self.store.save_revision(&previous).await?;
+            }
+            self.store.save_revision(&revision).await?;
+            info!(cluster, namespace, release = name, revision = revision.revision, chart, version, "Release deployed");
+            return Ok(revision);
+        };
+
+        revision.status = ReleaseStatus::Failed;
+        revision.description = reason.clone();
+        self.store.save_revision(&revision).await?;
+        warn!(cluster, namespace, release = name, revision = revision.revision, "Release failed: {}", reason);
+        let Some(previous) = previous else {
+            return Err(ContainerError::Deployment(format!("Release {} failed to install: {}", name, reason)));
+        };
+
+        let rolled_back = self.backend.rollback(&target, previous.revision, self.timeout).await.map_err(|e| {
+            ContainerError::Deployment(format!(
+                "Release {} failed ({}) and rolling back to revision {} also failed: {}",
+                name, reason, previous.revision, e
+            ))
+        })?;
+        let restored = ReleaseRevision {
+            revision: rolled_back.revision,
+            // The rollback re-applies the earlier revision, whose stored manifest is already masked
+            manifest: previous.manifest.clone(),
+            notes: previous.notes.clone(),
+            status: ReleaseStatus::Deployed,
+            description: format!("Rollback to {}", previous.revision),
+            deployed_at: Utc::now(),
+            ..previous.clone()
+        };
+        let mut superseded = previous.clone();
+        superseded.status = ReleaseStatus::Superseded;
+        self.store.save_revision(&superseded).await?;
+        self.store.save_revision(&restored).await?;
+        Err(ContainerError::Deployment(format!(
+            "Release {} revision {} failed and was rolled back to revision {}: {}",
+            name, revision.revision, previous.revision, reason
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    use serde_json::json;
+    use sirsi_key_vault::secret::{InMemorySecretManager, Secret, SecretValue};
+
+    use super::*;
+
+    // Renders each install as one ConfigMap holding the values, remembering what it was sent
+    #[derive(Default)]
+    struct FakeHelm {
+        revision: Mutex<u32>,
+        fail_next: Mutex<Option<String>>,
+        rendered: Mutex<Vec<Value>>,
+        rollbacks: Mutex<Vec<u32>>,
+    }
+
+    #[async_trait]
+    impl HelmBackend for FakeHelm {
+        async fn add_repository(&self, _repository: &ChartRepository) -> ContainerResult<()> {
+            Ok(())
+        }
+
+        async fn update_repositories(&self) -> ContainerResult<()> {
+            Ok(())
+        }
+
+        async fn upgrade_install(
+            &self,
+            target: &ReleaseTarget,
+            chart: &str,
+            version: &str,
+            values: &Value,
+            _timeout: Duration,
+        ) -> ContainerResult<HelmRelease> {
+            let mut revision = self.revision.lock().unwrap();
+            *revision += 1;
+            self.rendered.lock().unwrap().push(values.clone());
+            if let Some(error) = self.fail_next.lock().unwrap().take() {
+                return Err(ContainerError::Platform(error));
+            }
+            let manifest = format!(
+                "---\n# Source: {chart}/templates/config.yaml\napiVersion: v1\nkind: ConfigMap\nmetadata:\n  name: {}\ndata:\n  version: {version}\n  password: {}\n",
+                target.name,
+                values["db"]["password"].as_str().unwrap_or_default()
+            );
+            Ok(HelmRelease { revision: *revision, manifest, notes: None })
+        }
+
+        async fn rollback(&self, _target: &ReleaseTarget, revision: u32, _timeout: Duration) -> ContainerResult<HelmRelease> {
+            self.rollbacks.lock().unwrap().push(revision);
+            let mut current = self.revision.lock().unwrap();
+            *current += 1;
+            Ok(HelmRelease { revision: *current, manifest: String::new(), notes: None })
+        }
+    }
+
+    #[derive(Default)]
+    struct FakeHealth {
+        unhealthy: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl ReleaseHealth for FakeHealth {
+        async fn unhealthy_workloads(&self, _kubeconfig: &str, _namespace: &str, _release: &str) -> ContainerResult<Vec<String>> {
+            Ok(self.unhealthy.lock().unwrap().clone())
+        }
+    }
+
+    async fn secrets() -> Arc<InMemorySecretManager> {
+        let secrets = Arc::new(InMemorySecretManager::new());
+        let now = Utc::now();
+        secrets
+            .create_secret(Secret {
+                id: "shop/db".into(),
+                name: "shop db".into(),
+                description: None,
+                value: SecretValue::Plain("s3cr3t-pw".into()),
+                version: 0,
+                created_at: now,
+                updated_at: now,
+                expires_at: None,
+                metadata: HashMap::new(),
+                labels: HashMap::new(),
+                rotation_policy: None,
+            })
+            .await
+            .unwrap();
+        secrets
+    }
+
+    #[tokio::test]
+    async fn test_upgrade_rolls_back_unhealthy_release_without_storing_secrets() {
+        let helm = Arc::new(FakeHelm::default());
+        let health = Arc::new(FakeHealth::default());
+        let kubeconfigs = HashMap::from([("prod".to_string(), "apiVersion: v1".to_string())]);
+        let deployer = HelmDeployer::new(helm.clone(), Arc::new(kubeconfigs))
+            .with_secrets(secrets().await)
+            .with_health(health.clone())
+            .with_timeout(Duration::from_millis(20))
+            .with_poll_interval(Duration::from_millis(1));
+        let values = ValueOverrides::new().with_values(json!({ "db": { "password": "vault:shop/db" }, "replicas": 2 }));
+
+        let installed = deployer.install_release("prod", "shop", "checkout", "acme/checkout", "1.0.0", &values).await.unwrap();
+        assert_eq!(installed.revision, 1);
+        // Helm saw the secret, but neither the stored values nor the manifest hold it
+        assert_eq!(helm.rendered.lock().unwrap()[0]["db"]["password"], "s3cr3t-pw");
+        assert_eq!(installed.values["db"]["password"], "vault:shop/db");
+        assert!(installed.manifest.contains("password: <vault:shop/db>"));
+        assert!(deployer.install_release("prod", "shop", "checkout", "acme/checkout", "1.0.0", &values).await.is_err());
+
+        // Unhealthy workloads past the timeout roll the upgrade back
+        health.unhealthy.lock().unwrap().push("Deployment/checkout".into());
+        let err = deployer.upgrade_release("prod", "shop", "checkout", Some("1.1.0"), None).await.unwrap_err();
+        assert!(err.to_string().contains("rolled back to revision 1"));
+        assert_eq!(*helm.rollbacks.lock().unwrap(), vec![1]);
+
+        // A failed hook does the same; the history keeps every attempt
+        health.unhealthy.lock().unwrap().clear();
+        *helm.fail_next.lock().unwrap() = Some("pre-upgrade hook migrate failed: s3cr3t-pw rejected".into());
+        let err = deployer.upgrade_release("prod", "shop", "checkout", Some("1.2.0"), None).await.unwrap_err();
+        assert!(!err.to_string().contains("s3cr3t-pw"));
+
+        let upgraded = deployer
+            .upgrade_release("prod", "shop", "checkout", Some("1.3.0"), Some(&values.clone().with_set("replicas", 3)))
+            .await
+            .unwrap();
+        let history = deployer.release_history("prod", "shop", "checkout").await.unwrap();
+        let statuses: Vec<(u32, ReleaseStatus)> = history.iter().map(|r| (r.revision, r.status)).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                (1, ReleaseStatus::Superseded),
+                (2, ReleaseStatus::Failed),
+                (3, ReleaseStatus::Superseded),
+                (4, ReleaseStatus::Failed),
+                (5, ReleaseStatus::Superseded),
+                (6, ReleaseStatus::Deployed),
+            ]
+        );
+        assert_eq!(history[2].description, "Rollback to 1");
+        assert!(history.iter().all(|r| !r.description.contains("s3cr3t-pw") && !r.manifest.contains("s3cr3t-pw")));
+
+        let diff = deployer.diff_revisions("prod", "shop", "checkout", 1, upgraded.revision).await.unwrap();
+        assert_eq!(diff.chart, Some(("acme/checkout@1.0.0".into(), "acme/checkout@1.3.0".into())));
+        assert_eq!(diff.values, vec![ValueChange { path: "replicas".into(), before: Some(json!(2)), after: Some(json!(3)) }]);
+        assert_eq!(diff.resources.len(), 1);
+        assert_eq!(diff.resources[0].resource, "ConfigMap/checkout");
+        assert_eq!(diff.resources[0].lines, vec!["-  version: 1.0.0".to_string(), "+  version: 1.3.0".to_string()]);
+
+        let releases = deployer.list_releases(Some("prod")).await.unwrap();
+        assert_eq!(releases.len(), 1);
+        assert_eq!(releases[0].version, "1.3.0");
+    }
+}
@@ -0,0 +1,203 @@
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sirsi_key_vault::secret::SecretManager;
+use sirsi_key_vault::{Credential, KeyVaultError, SecretRef};
+
+use crate::error::{ContainerError, ContainerResult};
+
+// Release values on top of the chart's own defaults. Files merge in order, then each `set`
+// path is applied in order, so the last writer wins. Any string of the form
+// `vault:<secret id>` or `vault:<secret id>#<field>` is replaced by the secret at render time.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ValueOverrides {
+    pub files: Vec<Value>,
+    pub set: Vec<(String, Value)>,
+}
+
+impl ValueOverrides {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_values(mut self, values: Value) -> Self {
+        self.files.push(values);
+        self
+    }
+
+    // `path` is dot separated, e.g. `image.tag`
+    pub fn with_set(mut self, path: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.set.push((path.into(), value.into()));
+        self
+    }
+
+    pub fn merged(&self) -> ContainerResult<Value> {
+        let mut merged = Value::Object(Map::new());
+        for file in &self.files {
+            if !file.is_object() {
+                return Err(ContainerError::Validation("Helm values must be an object".into()));
+            }
+            merge(&mut merged, file);
+        }
+        for (path, value) in &self.set {
+            set_path(&mut merged, path, value.clone())?;
+        }
+        Ok(merged)
+    }
+}
+
+// Helm's coalescing rules: objects merge key by key and anything else replaces. A null is
+// kept rather than dropped so that helm removes the chart's default for that key as well.
+pub fn merge(base: &mut Value, overlay: &Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(key) {
+                    Some(existing) if existing.is_object() && value.is_object() => merge(existing, value),
+                    _ => {
+                        base.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay.clone(),
+    }
+}
+
+pub fn set_path(values: &mut Value, path: &str, value: Value) -> ContainerResult<()> {
+    let keys: Vec<&str> = path.split('.').collect();
+    if keys.iter().any(|k| k.is_empty()) {
+        return Err(ContainerError::Validation(format!("Invalid values path '{}'", path)));
+    }
+    let (last, parents) = keys.split_last().expect("split always yields a key");
+    let mut current = values;
+    for key in parents {
+        let object = current
+            .as_object_mut()
+            .ok_or_else(|| ContainerError::Validation(format!("'{}' crosses a non-object value", path)))?;
+        let next = object.entry(key.to_string()).or_insert_with(|| Value::Object(Map::new()));
+        if !next.is_object() {
+            *next = Value::Object(Map::new());
+        }
+        current = next;
+    }
+    let object = current
+        .as_object_mut()
+        .ok_or_else(|| ContainerError::Validation(format!("'{}' crosses a non-object value", path)))?;
+    object.insert(last.to_string(), value);
+    Ok(())
+}
+
+// A resolved secret and the forms it may take in a rendered manifest
+pub(crate) struct Redaction {
+    reference: String,
+    plaintext: String,
+}
+
+impl Redaction {
+    // Kubernetes Secrets carry values base64 encoded, so both forms are masked
+    pub fn apply(redactions: &[Redaction], text: &str) -> String {
+        let mut text = text.to_string();
+        for redaction in redactions.iter().filter(|r| !r.plaintext.is_empty()) {
+            let masked = format!("<{}>", redaction.reference);
+            let encoded = base64::engine::general_purpose::STANDARD.encode(&redaction.plaintext);
+            text = text.replace(&redaction.plaintext, &masked).replace(&encoded, &masked);
+        }
+        text
+    }
+}
+
+fn collect_refs(value: &Value, refs: &mut Vec<SecretRef>) {
+    match value {
+        Value::String(s) => refs.extend(SecretRef::parse(s)),
+        Value::Array(items) => items.iter().for_each(|v| collect_refs(v, refs)),
+        Value::Object(map) => map.values().for_each(|v| collect_refs(v, refs)),
+        _ => {}
+    }
+}
+
+fn substitute(value: &mut Value, resolved: &[(String, String)]) {
+    match value {
+        Value::String(s) => {
+            if let Some((_, plaintext)) = resolved.iter().find(|(reference, _)| reference == s) {
+                *s = plaintext.clone();
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|v| substitute(v, resolved)),
+        Value::Object(map) => map.values_mut().for_each(|v| substitute(v, resolved)),
+        _ => {}
+    }
+}
+
+// Returns the values with every vault reference replaced, plus what must be masked in
+// anything derived from them before it is stored
+pub(crate) async fn resolve_secrets(values: &Value, secrets: Option<&dyn SecretManager>) -> ContainerResult<(Value, Vec<Redaction>)> {
+    let mut refs = Vec::new();
+    collect_refs(values, &mut refs);
+    let mut resolved: Vec<(String, String)> = Vec::new();
+    for reference in refs {
+        let key = reference.to_string();
+        if resolved.iter().any(|(r, _)| *r == key) {
+            continue;
+        }
+        let secret = Credential::Vault(reference).resolve(secrets).await.map_err(|e| match e {
+            KeyVaultError::NotFound(msg) => ContainerError::NotFound(format!("Helm value {}: {}", key, msg)),
+            e => ContainerError::Config(format!("Failed to resolve helm value {}: {}", key, e)),
+        })?;
+        resolved.push((key, secret.expose().to_string()));
+    }
+    let mut rendered = values.clone();
+    substitute(&mut rendered, &resolved);
+    let redactions = resolved.into_iter().map(|(reference, plaintext)| Redaction { reference, plaintext }).collect();
+    Ok((rendered, redactions))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_values_merge_precedence() {
+        let overrides = ValueOverrides::new()
+            .with_values(json!({
+                "image": { "repository": "nginx", "tag": "1.25" },
+                "replicas": 1,
+                "ingress": { "enabled": true, "hosts": ["a.example"] },
+                "resources": { "limits": { "cpu": "500m" } },
+            }))
+            .with_values(json!({
+                "image": { "tag": "1.26" },
+                "ingress": { "hosts": ["b.example"] },
+                "resources": null,
+            }))
+            .with_set("replicas", 3)
+            .with_set("image.tag", "1.27")
+            .with_set("podLabels.team", "web");
+
+        assert_eq!(
+            overrides.merged().unwrap(),
+            json!({
+                // Later files merge into earlier ones and `set` paths win over both
+                "image": { "repository": "nginx", "tag": "1.27" },
+                "replicas": 3,
+                // Lists are replaced, not appended to
+                "ingress": { "enabled": true, "hosts": ["b.example"] },
+                "podLabels": { "team": "web" },
+                "resources": null,
+            })
+        );
+        assert!(ValueOverrides::new().with_set("image..tag", 1).merged().is_err());
+        assert!(ValueOverrides::new().with_values(json!(["not", "an", "object"])).merged().is_err());
+    }
+
+    #[test]
+    fn test_redaction_masks_plain_and_encoded_secrets() {
+        let redactions = vec![Redaction { reference: "vault:db/password".into(), plaintext: "hunter2".into() }];
+        let manifest = "env: hunter2\ndata:\n  password: aHVudGVyMg==\n";
+        assert_eq!(
+            Redaction::apply(&redactions, manifest),
+            "env: <vault:db/password>\ndata:\n  password: <vault:db/password>\n"
+        );
+    }
+}
@@ -0,0 +1,536 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::error::{ContainerError, ContainerResult};
+use crate::runtime::{Container, ContainerRuntime, ContainerState, RuntimeEvent, RuntimeEventKind};
+
+pub mod bluegreen;
+pub mod build;
+pub mod helm;
+pub mod usage;
+
+pub use bluegreen::{
+    DeploymentPhase, DeploymentRecord, DeploymentStore, InMemoryDeploymentStore, NetworkAttachmentSwitch, ProbeCheck,
+    ReadinessProbe, ServiceDefinition, ServiceTarget, TrafficSwitch,
+};
+pub use build::{
+    BuildBackend, BuildLogLine, BuildMeter, BuildRecord, BuildRequest, BuildService, BuildSource, BuildStatus, BuildStore,
+    BuildctlBackend, IndexPublisher, InMemoryBuildStore,
+};
+pub use helm::{
+    ChartRepository, HelmBackend, HelmCli, HelmDeployer, InMemoryReleaseStore, ReleaseDiff, ReleaseRevision, ReleaseStatus,
+    ReleaseStore, ValueOverrides,
+};
+pub use usage::{
+    ContainerEvent, ContainerEventKind, InMemoryUsageStore, PgUsageStore, UsageConfig, UsageHistory, UsageRollup,
+    UsageSample, UsageStore,
+};
+
+use usage::{hour_start, TrackedContainer};
+
+pub const MANAGED_BY_LABEL: &str = "app.kubernetes.io/managed-by";
+pub const MANAGED_BY_VALUE: &str = "sirsi-nexus";
+
+pub struct ContainerService {
+    runtime: Arc<dyn ContainerRuntime>,
+    store: Arc<dyn UsageStore>,
+    config: UsageConfig,
+    tracked: Mutex<HashMap<String, TrackedContainer>>,
+    deployments: Arc<dyn DeploymentStore>,
+    traffic: Option<Arc<dyn TrafficSwitch>>,
+    helm: Option<Arc<HelmDeployer>>,
+}
+
+impl ContainerService {
+    pub fn new(runtime: Arc<dyn ContainerRuntime>) -> Self {
+        Self {
+            runtime,
+            store: Arc::new(InMemoryUsageStore::new()),
+            config: UsageConfig::default(),
+            tracked: Mutex::new(HashMap::new()),
+            deployments: Arc::new(InMemoryDeploymentStore::new()),
+            traffic: None,
+            helm: None,
+        }
+    }
+
+    pub fn with_usage_store(mut self, store: Arc<dyn UsageStore>) -> Self {
+        self.store = store;
+        self
+    }
+
+    pub fn with_usage_config(mut self, config: UsageConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn with_deployment_store(mut self, store: Arc<dyn DeploymentStore>) -> Self {
+        self.deployments = store;
+        self
+    }
+
+    pub fn with_traffic_switch(mut self, traffic: Arc<dyn TrafficSwitch>) -> Self {
+        self.traffic = Some(traffic);
+        self
+    }
+
+    pub fn with_helm(mut self, helm: Arc<HelmDeployer>) -> Self {
+        self.helm = Some(helm);
+        self
+    }
+
+    pub fn runtime(&self) -> &Arc<dyn ContainerRuntime> {
+        &self.runtime
+    }
+
+    pub fn helm(&self) -> ContainerResult<&Arc<HelmDeployer>> {
+        self.helm
+            .as_ref()
+            .ok_or_else(|| ContainerError::Config("No Helm deployer configured".to_string()))
+    }
+
+    fn is_managed(container: &Container) -> bool {
+        container.labels.get(MANAGED_BY_LABEL).map(String::as_str) == Some(MANAGED_BY_VALUE)
+    }
+
+    // Polls stats for every running managed container and returns how many were sampled
+    pub async fn sample_usage(&self, now: DateTime<Utc>) -> ContainerResult<usize> {
+        let containers: Vec<Container> = self
+            .runtime
+            .list_containers()
+            .await?
+            .into_iter()
+            .filter(|c| Self::is_managed(c) && matches!(c.state, ContainerState::Running))
+            .collect();
+
+        let mut sampled = Vec::new();
+        for container in &containers {
+            match self.runtime.container_stats(&container.id).await {
+                Ok(stats) => sampled.push((container.id.clone(), UsageSample::from_stats(&stats, now))),
+                Err(e) => warn!("Failed to collect stats for container {}: {}", container.id, e),
+            }
+        }
+
+        let mut tracked = self.tracked.lock().await;
+        let running: HashSet<&str> = containers.iter().map(|c| c.id.as_str()).collect();
+        tracked.retain(|id, _| running.contains(id.as_str()));
+
+        let mut rollups = Vec::new();
+        let mut events = Vec::new();
+        for (id, sample) in &sampled {
+            let state = tracked.entry(id.clone()).or_default();
+            let hour = hour_start(sample.at);
+            if state.open.as_ref().is_none_or(|r| r.hour != hour) {
+                // A restarted sampler resumes the partial hour it already persisted
+                state.open = Some(
+                    self.store
+                        .get_rollup(id, hour)
+                        .await?
+                        .unwrap_or_else(|| UsageRollup::new(id.clone(), hour)),
+                );
+            }
+            if let Some(open) = state.open.as_mut() {
+                open.add(sample);
+                rollups.push(open.clone());
+            }
+            state.push(sample.clone(), self.config.ring_capacity);
+            if state.observe_pressure(sample, &self.config) {
+                events.push(ContainerEvent {
+                    container_id: id.clone(),
+                    kind: ContainerEventKind::MemoryPressure,
+                    at: sample.at,
+                    exit_code: None,
+                    memory_usage: Some(sample.memory_usage),
+                    memory_limit: Some(sample.memory_limit),
+                    message: format!(
+                        "Memory above {:.0}% of limit for {}s",
+                        self.config.pressure_threshold * 100.0,
+                        self.config.pressure_duration.as_secs()
+                    ),
+                });
+            }
+        }
+        drop(tracked);
+
+        for rollup in &rollups {
+            self.store.save_rollup(rollup).await?;
+        }
+        for event in &events {
+            warn!("Container {}: {}", event.container_id, event.message);
+            self.store.record_event(event).await?;
+        }
+        Ok(sampled.len())
+    }
+
+    pub async fn handle_runtime_event(&self, event: RuntimeEvent) -> ContainerResult<()> {
+        let mut tracked = self.tracked.lock().await;
+        let last = tracked.get(&event.container_id).and_then(|s| s.last_sample()).cloned();
+        let recorded = match event.kind {
+            RuntimeEventKind::Oom => {
+                tracked.entry(event.container_id.clone()).or_default().last_oom = Some(event.at);
+                Some(ContainerEvent {
+                    container_id: event.container_id.clone(),
+                    kind: ContainerEventKind::OomKilled,
+                    at: event.at,
+                    exit_code: None,
+                    memory_usage: last.as_ref().map(|s| s.memory_usage),
+                    memory_limit: last.as_ref().map(|s| s.memory_limit),
+                    message: "Container was killed by the out-of-memory killer".to_string(),
+                })
+            }
+            RuntimeEventKind::Die { exit_code } => {
+                let oom_kill = tracked
+                    .get(&event.container_id)
+                    .and_then(|s| s.last_oom)
+                    .and_then(|oom| (event.at - oom).to_std().ok())
+                    .is_some_and(|elapsed| elapsed <= self.config.oom_exit_window);
+                // The OOM event already describes this exit
+                (!oom_kill).then(|| ContainerEvent {
+                    container_id: event.container_id.clone(),
+                    kind: ContainerEventKind::Exited,
+                    at: event.at,
+                    exit_code: Some(exit_code),
+                    memory_usage: last.as_ref().map(|s| s.memory_usage),
+                    memory_limit: last.as_ref().map(|s| s.memory_limit),
+                    message: format!("Container exited with code {}", exit_code),
+                })
+            }
+            RuntimeEventKind::Restart => Some(ContainerEvent {
+                container_id: event.container_id.clone(),
+                kind: ContainerEventKind::Restarted,
+                at: event.at,
+                exit_code: None,
+                memory_usage: None,
+                memory_limit: None,
+                message: "Container was restarted".to_string(),
+            }),
+            RuntimeEventKind::Start => None,
+        };
+        drop(tracked);
+
+        if let Some(recorded) = recorded {
+            info!("Container {}: {}", recorded.container_id, recorded.message);
+            self.store.record_event(&recorded).await?;
+        }
+        Ok(())
+    }
+
+    pub fn spawn_stats_sampler(self: Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = self.sample_usage(Utc::now()).await {
+                    warn!("Container usage sampling failed: {}", e);
+                }
+            }
+        })
+    }
+
+    pub fn spawn_event_watcher(self: Arc<Self>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut events = match self.runtime.container_events().await {
+                Ok(events) => events,
+                Err(e) => {
+                    warn!("Failed to subscribe to runtime events: {}", e);
+                    return;
+                }
+            };
+            while let Some(event) = events.recv().await {
+                if let Err(e) = self.handle_runtime_event(event).await {
+                    warn!("Failed to record runtime event: {}", e);
+                }
+            }
+            info!("Runtime event stream closed");
+        })
+    }
+
+    pub async fn get_usage_history(&self, container_id: &str, window: Duration) -> ContainerResult<UsageHistory> {
+        self.usage_history_at(container_id, window, Utc::now()).await
+    }
+
+    async fn usage_history_at(
+        &self,
+        container_id: &str,
+        window: Duration,
+        now: DateTime<Utc>,
+    ) -> ContainerResult<UsageHistory> {
+        let window = chrono::Duration::from_std(window)
+            .map_err(|_| ContainerError::Validation(format!("Usage window {:?} is too large", window)))?;
+        let since = now - window;
+        let samples: Vec<UsageSample> = self
+            .tracked
+            .lock()
+            .await
+            .get(container_id)
+            .map(|s| s.samples.iter().filter(|s| s.at >= since).cloned().collect())
+            .unwrap_or_default();
+        let rollups = self.store.list_rollups(container_id, since).await?;
+        if samples.is_empty() && rollups.is_empty() {
+            return Err(ContainerError::NotFound(format!("No usage recorded for container {}", container_id)));
+        }
+        Ok(UsageHistory {
+            container_id: container_id.to_string(),
+            samples,
+            rollups,
+        })
+    }
+
+    pub async fn list_events(&self, container_id: &str) -> ContainerResult<Vec<ContainerEvent>> {
+        self.store.list_events(container_id).await
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::runtime::{ContainerConfig, ContainerStats, ExecResult};
+    use async_trait::async_trait;
+    use chrono::TimeZone;
+    use std::net::SocketAddr;
+    use tokio::sync::mpsc;
+
+    const GIB: u64 = 1024 * 1024 * 1024;
+
+    #[derive(Default)]
+    pub(crate) struct MockRuntime {
+        containers: std::sync::Mutex<Vec<Container>>,
+        stats: std::sync::Mutex<HashMap<String, ContainerStats>>,
+        next_id: std::sync::atomic::AtomicUsize,
+        pub fail_next_start: std::sync::atomic::AtomicBool,
+    }
+
+    impl MockRuntime {
+        pub fn add(&self, id: &str, managed: bool) {
+            let mut labels = HashMap::new();
+            if managed {
+                labels.insert(MANAGED_BY_LABEL.to_string(), MANAGED_BY_VALUE.to_string());
+            }
+            self.add_with_labels(id, labels);
+        }
+
+        pub fn add_with_labels(&self, id: &str, labels: HashMap<String, String>) {
+            self.containers.lock().unwrap().push(Container {
+                id: id.to_string(),
+                name: id.to_string(),
+                image: "nginx:1.25".to_string(),
+                state: ContainerState::Running,
+                created: Utc::now(),
+                started: Some(Utc::now()),
+                finished: None,
+                exit_code: None,
+                labels,
+            });
+        }
+
+        pub fn ids(&self) -> Vec<String> {
+            self.containers.lock().unwrap().iter().map(|c| c.id.clone()).collect()
+        }
+
+        fn set_usage(&self, id: &str, cpu_usage: f64, memory_usage: u64) {
+            self.stats.lock().unwrap().insert(
+                id.to_string(),
+                ContainerStats {
+                    cpu_usage,
+                    memory_usage,
+                    memory_limit: 4 * GIB,
+                    network_rx_bytes: 0,
+                    network_tx_bytes: 0,
+                    block_rx_bytes: 0,
+                    block_tx_bytes: 0,
+                },
+            );
+        }
+    }
+
+    #[async_trait]
+    impl ContainerRuntime for MockRuntime {
+        async fn create_container(&self, config: ContainerConfig) -> ContainerResult<Container> {
+            let n = self.next_id.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            let container = Container {
+                id: format!("green-{}", n),
+                name: format!("green-{}", n),
+                image: config.image,
+                state: ContainerState::Created,
+                created: Utc::now(),
+                started: None,
+                finished: None,
+                exit_code: None,
+                labels: config.labels.unwrap_or_default(),
+            };
+            self.containers.lock().unwrap().push(container.clone());
+            Ok(container)
+        }
+        async fn start_container(&self, id: &str) -> ContainerResult<()> {
+            if self.fail_next_start.swap(false, std::sync::atomic::Ordering::SeqCst) {
+                return Err(ContainerError::Platform("runtime went away".to_string()));
+            }
+            let mut containers = self.containers.lock().unwrap();
+            let container = containers
+                .iter_mut()
+                .find(|c| c.id == id)
+                .ok_or_else(|| ContainerError::NotFound(id.to_string()))?;
+            container.state = ContainerState::Running;
+            Ok(())
+        }
+        async fn stop_container(&self, _id: &str) -> ContainerResult<()> {
+            Ok(())
+        }
+        async fn remove_container(&self, id: &str) -> ContainerResult<()> {
+            self.containers.lock().unwrap().retain(|c| c.id != id);
+            Ok(())
+        }
+        async fn get_container(&self, id: &str) -> ContainerResult<Container> {
+            self.containers
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|c| c.id == id)
+                .cloned()
+                .ok_or_else(|| ContainerError::NotFound(id.to_string()))
+        }
+        async fn list_containers(&self) -> ContainerResult<Vec<Container>> {
+            Ok(self.containers.lock().unwrap().clone())
+        }
+        async fn container_logs(&self, _id: &str) -> ContainerResult<Vec<String>> {
+            Ok(Vec::new())
+        }
+        async fn container_stats(&self, id: &str) -> ContainerResult<ContainerStats> {
+            self.stats
+                .lock()
+                .unwrap()
+                .get(id)
+                .cloned()
+                .ok_or_else(|| ContainerError::NotFound(id.to_string()))
+        }
+        async fn exec_in_container(&self, id: &str, _cmd: Vec<String>) -> ContainerResult<ExecResult> {
+            let container = self.get_container(id).await?;
+            Ok(ExecResult {
+                exit_code: if container.image.contains("broken") { 1 } else { 0 },
+                stdout: String::new(),
+                stderr: String::new(),
+            })
+        }
+        async fn container_address(&self, id: &str, container_port: i32) -> ContainerResult<SocketAddr> {
+            self.get_container(id).await?;
+            let offset = id.bytes().fold(0u16, |acc, b| acc.wrapping_mul(31).wrapping_add(b as u16)) % 10000;
+            Ok(SocketAddr::from(([127, 0, 0, 1], 30000 + offset + container_port as u16 % 100)))
+        }
+        async fn container_events(&self) -> ContainerResult<mpsc::Receiver<RuntimeEvent>> {
+            let (_tx, rx) = mpsc::channel(1);
+            Ok(rx)
+        }
+    }
+
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, 1, hour, minute, 0).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_hourly_rollups() {
+        let runtime = Arc::new(MockRuntime::default());
+        runtime.add("web", true);
+        runtime.add("sidecar", false);
+        runtime.set_usage("sidecar", 0.1, GIB);
+        let store = Arc::new(InMemoryUsageStore::new());
+        let service = ContainerService::new(runtime.clone())
+            .with_usage_store(store.clone())
+            .with_usage_config(UsageConfig { ring_capacity: 3, ..UsageConfig::default() });
+
+        for (minute, cpu, memory) in [(0, 0.5, GIB), (20, 1.5, 3 * GIB), (40, 1.0, 2 * GIB)] {
+            runtime.set_usage("web", cpu, memory);
+            assert_eq!(service.sample_usage(at(10, minute)).await.unwrap(), 1);
+        }
+        runtime.set_usage("web", 2.0, GIB);
+        service.sample_usage(at(11, 5)).await.unwrap();
+
+        let history = service.usage_history_at("web", Duration::from_secs(3 * 3600), at(11, 5)).await.unwrap();
+        assert_eq!(history.samples.len(), 3);
+        assert_eq!(history.samples[0].at, at(10, 20));
+        assert_eq!(history.rollups.len(), 2);
+        let first = &history.rollups[0];
+        assert_eq!(first.hour, at(10, 0));
+        assert_eq!(first.samples, 3);
+        assert!((first.cpu_avg() - 1.0).abs() < 1e-9);
+        assert_eq!(first.cpu_max, 1.5);
+        assert_eq!((first.memory_min, first.memory_avg(), first.memory_max), (GIB, 2 * GIB, 3 * GIB));
+        assert_eq!(history.rollups[1].samples, 1);
+
+        // A fresh sampler picks up the persisted partial hour instead of overwriting it
+        let restarted = ContainerService::new(runtime.clone()).with_usage_store(store.clone());
+        runtime.set_usage("web", 4.0, 3 * GIB);
+        restarted.sample_usage(at(11, 10)).await.unwrap();
+        let current = store.get_rollup("web", at(11, 0)).await.unwrap().unwrap();
+        assert_eq!(current.samples, 2);
+        assert!((current.cpu_avg() - 3.0).abs() < 1e-9);
+
+        assert!(matches!(
+            service.usage_history_at("sidecar", Duration::from_secs(3600), at(11, 5)).await,
+            Err(ContainerError::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_oom_attribution_and_pressure() {
+        let runtime = Arc::new(MockRuntime::default());
+        runtime.add("api", true);
+        runtime.add("worker", true);
+        runtime.set_usage("worker", 0.2, GIB);
+        let service = ContainerService::new(runtime.clone());
+
+        // Above 90% for five minutes warns once, even though pressure continues
+        for minute in [0, 3, 5, 6] {
+            runtime.set_usage("api", 1.0, 4 * GIB - GIB / 4);
+            service.sample_usage(at(12, minute)).await.unwrap();
+        }
+        let events = service.list_events("api").await.unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, ContainerEventKind::MemoryPressure);
+        assert_eq!(events[0].at, at(12, 5));
+
+        let oom_at = at(12, 7);
+        service
+            .handle_runtime_event(RuntimeEvent { container_id: "api".to_string(), kind: RuntimeEventKind::Oom, at: oom_at })
+            .await
+            .unwrap();
+        for (id, offset) in [("api", 1), ("worker", 1)] {
+            service
+                .handle_runtime_event(RuntimeEvent {
+                    container_id: id.to_string(),
+                    kind: RuntimeEventKind::Die { exit_code: 137 },
+                    at: oom_at + chrono::Duration::seconds(offset),
+                })
+                .await
+                .unwrap();
+        }
+        service
+            .handle_runtime_event(RuntimeEvent {
+                container_id: "api".to_string(),
+                kind: RuntimeEventKind::Restart,
+                at: oom_at + chrono::Duration::seconds(2),
+            })
+            .await
+            .unwrap();
+
+        let kinds: Vec<ContainerEventKind> = service.list_events("api").await.unwrap().iter().map(|e| e.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![ContainerEventKind::MemoryPressure, ContainerEventKind::OomKilled, ContainerEventKind::Restarted]
+        );
+        let oom = &service.list_events("api").await.unwrap()[1];
+        assert_eq!(oom.memory_usage, Some(4 * GIB - GIB / 4));
+        assert_eq!(oom.memory_limit, Some(4 * GIB));
+
+        let worker = service.list_events("worker").await.unwrap();
+        assert_eq!(worker.len(), 1);
+        assert_eq!(worker[0].kind, ContainerEventKind::Exited);
+        assert_eq!(worker[0].exit_code, Some(137));
+        assert_eq!(worker[0].memory_usage, Some(GIB));
+    }
+}
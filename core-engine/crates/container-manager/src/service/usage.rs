@@ -0,0 +1,397 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Row};
+use tokio::sync::RwLock;
+
+use crate::error::{ContainerError, ContainerResult};
+use crate::runtime::ContainerStats;
+
+const HOUR_SECONDS: i64 = 3600;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageConfig {
+    // Recent samples kept per container before they only survive in the hourly rollups
+    pub ring_capacity: usize,
+    pub pressure_threshold: f64,
+    pub pressure_duration: Duration,
+    // A die reported this soon after an OOM for the same container is the OOM kill itself
+    pub oom_exit_window: Duration,
+}
+
+impl Default for UsageConfig {
+    fn default() -> Self {
+        Self {
+            ring_capacity: 240,
+            pressure_threshold: 0.9,
+            pressure_duration: Duration::from_secs(300),
+            oom_exit_window: Duration::from_secs(5),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UsageSample {
+    pub at: DateTime<Utc>,
+    pub cpu_usage: f64,
+    pub memory_usage: u64,
+    pub memory_limit: u64,
+}
+
+impl UsageSample {
+    pub fn from_stats(stats: &ContainerStats, at: DateTime<Utc>) -> Self {
+        Self {
+            at,
+            cpu_usage: stats.cpu_usage,
+            memory_usage: stats.memory_usage,
+            memory_limit: stats.memory_limit,
+        }
+    }
+
+    // None when the container runs without a memory limit
+    pub fn memory_fraction(&self) -> Option<f64> {
+        (self.memory_limit > 0).then(|| self.memory_usage as f64 / self.memory_limit as f64)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UsageRollup {
+    pub container_id: String,
+    pub hour: DateTime<Utc>,
+    pub samples: u64,
+    pub cpu_sum: f64,
+    pub cpu_max: f64,
+    pub memory_sum: u64,
+    pub memory_min: u64,
+    pub memory_max: u64,
+    pub memory_limit: u64,
+}
+
+impl UsageRollup {
+    pub fn new(container_id: impl Into<String>, hour: DateTime<Utc>) -> Self {
+        Self {
+            container_id: container_id.into(),
+            hour,
+            samples: 0,
+            cpu_sum: 0.0,
+            cpu_max: 0.0,
+            memory_sum: 0,
+            memory_min: 0,
+            memory_max: 0,
+            memory_limit: 0,
+        }
+    }
+
+    pub fn add(&mut self, sample: &UsageSample) {
+        self.memory_min = if self.samples == 0 {
+            sample.memory_usage
+        } else {
+            self.memory_min.min(sample.memory_usage)
+        };
+        self.samples += 1;
+        self.cpu_sum += sample.cpu_usage;
+        self.cpu_max = self.cpu_max.max(sample.cpu_usage);
+        self.memory_sum = self.memory_sum.saturating_add(sample.memory_usage);
+        self.memory_max = self.memory_max.max(sample.memory_usage);
+        self.memory_limit = sample.memory_limit;
+    }
+
+    pub fn cpu_avg(&self) -> f64 {
+        if self.samples == 0 {
+            return 0.0;
+        }
+        self.cpu_sum / self.samples as f64
+    }
+
+    pub fn memory_avg(&self) -> u64 {
+        if self.samples == 0 {
+            return 0;
+        }
+        self.memory_sum / self.samples
+    }
+}
+
+pub fn hour_start(at: DateTime<Utc>) -> DateTime<Utc> {
+    let seconds = at.timestamp();
+    Utc.timestamp_opt(seconds - seconds.rem_euclid(HOUR_SECONDS), 0)
+        .single()
+        .unwrap_or(at)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ContainerEventKind {
+    OomKilled,
+    Restarted,
+    Exited,
+    MemoryPressure,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContainerEvent {
+    pub container_id: String,
+    pub kind: ContainerEventKind,
+    pub at: DateTime<Utc>,
+    pub exit_code: Option<i32>,
+    pub memory_usage: Option<u64>,
+    pub memory_limit: Option<u64>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageHistory {
+    pub container_id: String,
+    pub samples: Vec<UsageSample>,
+    pub rollups: Vec<UsageRollup>,
+}
+
+// Durable storage for hourly rollups and container events
+#[async_trait]
+pub trait UsageStore: Send + Sync {
+    async fn save_rollup(&self, rollup: &UsageRollup) -> ContainerResult<()>;
+    async fn get_rollup(&self, container_id: &str, hour: DateTime<Utc>) -> ContainerResult<Option<UsageRollup>>;
+    async fn list_rollups(&self, container_id: &str, since: DateTime<Utc>) -> ContainerResult<Vec<UsageRollup>>;
+    async fn record_event(&self, event: &ContainerEvent) -> ContainerResult<()>;
+    async fn list_events(&self, container_id: &str) -> ContainerResult<Vec<ContainerEvent>>;
+}
+
+#[derive(Default)]
+pub struct InMemoryUsageStore {
+    rollups: RwLock<HashMap<(String, DateTime<Utc>), UsageRollup>>,
+    events: RwLock<Vec<ContainerEvent>>,
+}
+
+impl InMemoryUsageStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl UsageStore for InMemoryUsageStore {
+    async fn save_rollup(&self, rollup: &UsageRollup) -> ContainerResult<()> {
+        self.rollups
+            .write()
+            .await
+            .insert((rollup.container_id.clone(), rollup.hour), rollup.clone());
+        Ok(())
+    }
+
+    async fn get_rollup(&self, container_id: &str, hour: DateTime<Utc>) -> ContainerResult<Option<UsageRollup>> {
+        Ok(self.rollups.read().await.get(&(container_id.to_string(), hour)).cloned())
+    }
+
+    async fn list_rollups(&self, container_id: &str, since: DateTime<Utc>) -> ContainerResult<Vec<UsageRollup>> {
+        let mut rollups: Vec<UsageRollup> = self
+            .rollups
+            .read()
+            .await
+            .values()
+            .filter(|r| r.container_id == container_id && r.hour >= hour_start(since))
+            .cloned()
+            .collect();
+        rollups.sort_by_key(|r| r.hour);
+        Ok(rollups)
+    }
+
+    async fn record_event(&self, event: &ContainerEvent) -> ContainerResult<()> {
+        self.events.write().await.push(event.clone());
+        Ok(())
+    }
+
+    async fn list_events(&self, container_id: &str) -> ContainerResult<Vec<ContainerEvent>> {
+        let mut events: Vec<ContainerEvent> = self
+            .events
+            .read()
+            .await
+            .iter()
+            .filter(|e| e.container_id == container_id)
+            .cloned()
+            .collect();
+        events.sort_by_key(|e| e.at);
+        Ok(events)
+    }
+}
+
+pub struct PgUsageStore {
+    pool: PgPool,
+}
+
+impl PgUsageStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn ensure_schema(&self) -> ContainerResult<()> {
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS container_usage_rollups (
+                container_id TEXT NOT NULL,
+                hour_start BIGINT NOT NULL,
+                samples BIGINT NOT NULL,
+                cpu_sum DOUBLE PRECISION NOT NULL,
+                cpu_max DOUBLE PRECISION NOT NULL,
+                memory_sum BIGINT NOT NULL,
+                memory_min BIGINT NOT NULL,
+                memory_max BIGINT NOT NULL,
+                memory_limit BIGINT NOT NULL,
+                PRIMARY KEY (container_id, hour_start)
+            )",
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS container_events (
+                id BIGSERIAL PRIMARY KEY,
+                container_id TEXT NOT NULL,
+                at_ms BIGINT NOT NULL,
+                event JSONB NOT NULL
+            )",
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS container_events_container_idx ON container_events (container_id, at_ms)")
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    fn rollup_from_row(row: &sqlx::postgres::PgRow) -> ContainerResult<UsageRollup> {
+        let hour: i64 = row.try_get("hour_start")?;
+        let hour = Utc
+            .timestamp_opt(hour, 0)
+            .single()
+            .ok_or_else(|| ContainerError::Internal(format!("Invalid rollup hour {}", hour)))?;
+        let unsigned = |column: &str| -> ContainerResult<u64> { Ok(row.try_get::<i64, _>(column)?.max(0) as u64) };
+        Ok(UsageRollup {
+            container_id: row.try_get("container_id")?,
+            hour,
+            samples: unsigned("samples")?,
+            cpu_sum: row.try_get("cpu_sum")?,
+            cpu_max: row.try_get("cpu_max")?,
+            memory_sum: unsigned("memory_sum")?,
+            memory_min: unsigned("memory_min")?,
+            memory_max: unsigned("memory_max")?,
+            memory_limit: unsigned("memory_limit")?,
+        })
+    }
+}
+
+fn to_bigint(value: u64) -> i64 {
+    value.min(i64::MAX as u64) as i64
+}
+
+#[async_trait]
+impl UsageStore for PgUsageStore {
+    async fn save_rollup(&self, rollup: &UsageRollup) -> ContainerResult<()> {
+        sqlx::query(
+            "INSERT INTO container_usage_rollups
+                (container_id, hour_start, samples, cpu_sum, cpu_max, memory_sum, memory_min, memory_max, memory_limit)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+             ON CONFLICT (container_id, hour_start) DO UPDATE SET
+                samples = EXCLUDED.samples,
+                cpu_sum = EXCLUDED.cpu_sum,
+                cpu_max = EXCLUDED.cpu_max,
+                memory_sum = EXCLUDED.memory_sum,
+                memory_min = EXCLUDED.memory_min,
+                memory_max = EXCLUDED.memory_max,
+                memory_limit = EXCLUDED.memory_limit",
+        )
+        .bind(&rollup.container_id)
+        .bind(rollup.hour.timestamp())
+        .bind(to_bigint(rollup.samples))
+        .bind(rollup.cpu_sum)
+        .bind(rollup.cpu_max)
+        .bind(to_bigint(rollup.memory_sum))
+        .bind(to_bigint(rollup.memory_min))
+        .bind(to_bigint(rollup.memory_max))
+        .bind(to_bigint(rollup.memory_limit))
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn get_rollup(&self, container_id: &str, hour: DateTime<Utc>) -> ContainerResult<Option<UsageRollup>> {
+        let row = sqlx::query("SELECT * FROM container_usage_rollups WHERE container_id = $1 AND hour_start = $2")
+            .bind(container_id)
+            .bind(hour.timestamp())
+            .fetch_optional(&self.pool)
+            .await?;
+        row.as_ref().map(Self::rollup_from_row).transpose()
+    }
+
+    async fn list_rollups(&self, container_id: &str, since: DateTime<Utc>) -> ContainerResult<Vec<UsageRollup>> {
+        let rows = sqlx::query(
+            "SELECT * FROM container_usage_rollups WHERE container_id = $1 AND hour_start >= $2 ORDER BY hour_start",
+        )
+        .bind(container_id)
+        .bind(hour_start(since).timestamp())
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(Self::rollup_from_row).collect()
+    }
+
+    async fn record_event(&self, event: &ContainerEvent) -> ContainerResult<()> {
+        sqlx::query("INSERT INTO container_events (container_id, at_ms, event) VALUES ($1, $2, $3)")
+            .bind(&event.container_id)
+            .bind(event.at.timestamp_millis())
+            .bind(sqlx::types::Json(event))
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn list_events(&self, container_id: &str) -> ContainerResult<Vec<ContainerEvent>> {
+        let rows = sqlx::query("SELECT event FROM container_events WHERE container_id = $1 ORDER BY at_ms, id")
+            .bind(container_id)
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter()
+            .map(|row| {
+                let sqlx::types::Json(event) = row.try_get::<sqlx::types::Json<ContainerEvent>, _>("event")?;
+                Ok(event)
+            })
+            .collect()
+    }
+}
+
+// Per-container sampling state held between polls
+#[derive(Debug, Default)]
+pub(crate) struct TrackedContainer {
+    pub samples: VecDeque<UsageSample>,
+    pub open: Option<UsageRollup>,
+    pub pressure_since: Option<DateTime<Utc>>,
+    pub pressure_reported: bool,
+    pub last_oom: Option<DateTime<Utc>>,
+}
+
+impl TrackedContainer {
+    pub fn push(&mut self, sample: UsageSample, capacity: usize) {
+        if self.samples.len() >= capacity.max(1) {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    pub fn last_sample(&self) -> Option<&UsageSample> {
+        self.samples.back()
+    }
+
+    // Returns when sustained pressure first crosses the configured duration
+    pub fn observe_pressure(&mut self, sample: &UsageSample, config: &UsageConfig) -> bool {
+        let over = sample.memory_fraction().is_some_and(|f| f > config.pressure_threshold);
+        if !over {
+            self.pressure_since = None;
+            self.pressure_reported = false;
+            return false;
+        }
+        let since = *self.pressure_since.get_or_insert(sample.at);
+        let sustained = (sample.at - since).to_std().unwrap_or_default() >= config.pressure_duration;
+        if sustained && !self.pressure_reported {
+            self.pressure_reported = true;
+            return true;
+        }
+        false
+    }
+}
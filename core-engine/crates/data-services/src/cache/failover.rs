@@ -0,0 +1,674 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast, Mutex, RwLock};
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+
+use crate::error::{DataError, DataResult};
+use super::{CacheNode, EventNotification, EventSeverity, EventType, NodeRole, NodeStatus};
+
+// A fenced master refuses writes because it can never have this many replicas attached
+pub const FENCE_MIN_REPLICAS: &str = "1000000";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplicationInfo {
+    pub role: NodeRole,
+    pub offset: u64,
+    pub master_link_up: bool,
+}
+
+impl ReplicationInfo {
+    // Parses the `INFO replication` section
+    pub fn parse(info: &str) -> DataResult<Self> {
+        let fields: HashMap<&str, &str> = info
+            .lines()
+            .filter_map(|line| line.trim().split_once(':'))
+            .collect();
+        let role = match fields.get("role") {
+            Some(&"master") => NodeRole::Primary,
+            Some(&"slave") | Some(&"replica") => NodeRole::Replica,
+            other => return Err(DataError::Cache(format!("Unexpected replication role {:?}", other))),
+        };
+        let offset_field = match role {
+            NodeRole::Primary => "master_repl_offset",
+            NodeRole::Replica => "slave_repl_offset",
+        };
+        let offset = fields
+            .get(offset_field)
+            .and_then(|v| v.parse().ok())
+            .ok_or_else(|| DataError::Cache(format!("INFO replication has no {}", offset_field)))?;
+        Ok(Self {
+            role,
+            offset,
+            master_link_up: fields.get("master_link_status") == Some(&"up"),
+        })
+    }
+}
+
+// Per-node Redis administration. Unreachable nodes fail with DataError::Unavailable.
+#[async_trait]
+pub trait RedisAdmin: Send + Sync {
+    async fn ping(&self, node: &CacheNode) -> DataResult<()>;
+    async fn replication_info(&self, node: &CacheNode) -> DataResult<ReplicationInfo>;
+    async fn config_set(&self, node: &CacheNode, parameter: &str, value: &str) -> DataResult<()>;
+    async fn config_rewrite(&self, node: &CacheNode) -> DataResult<()>;
+    // Disconnects regular clients; replication links stay up
+    async fn kill_clients(&self, node: &CacheNode) -> DataResult<()>;
+    // None promotes the node (REPLICAOF NO ONE)
+    async fn replicate_from(&self, node: &CacheNode, primary: Option<&CacheNode>) -> DataResult<()>;
+}
+
+pub struct RedisCommandAdmin {
+    password: Option<String>,
+    timeout: Duration,
+}
+
+impl Default for RedisCommandAdmin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RedisCommandAdmin {
+    pub fn new() -> Self {
+        Self {
+            password: None,
+            timeout: Duration::from_secs(2),
+        }
+    }
+
+    pub fn with_password(mut self, password: impl Into<String>) -> Self {
+        self.password = Some(password.into());
+        self
+    }
+
+    pub(crate) async fn query<T: redis::FromRedisValue>(&self, node: &CacheNode, cmd: &redis::Cmd) -> DataResult<T> {
+        let info = redis::ConnectionInfo {
+            addr: redis::ConnectionAddr::Tcp(node.address.clone(), node.port),
+            redis: redis::RedisConnectionInfo {
+                db: 0,
+                username: None,
+                password: self.password.clone(),
+            },
+        };
+        let unavailable = |e: String| DataError::Unavailable(format!("Cache node {} unreachable: {}", node.id, e));
+        let client = redis::Client::open(info).map_err(|e| DataError::Config(e.to_string()))?;
+        let run = async {
+            let mut conn = client
+                .get_multiplexed_async_connection()
+                .await
+                .map_err(|e| unavailable(e.to_string()))?;
+            cmd.query_async::<_, T>(&mut conn).await.map_err(|e| {
+                if e.is_io_error() || e.is_connection_dropped() {
+                    unavailable(e.to_string())
+                } else {
+                    DataError::Cache(format!("Command on {} failed: {}", node.id, e))
+                }
+            })
+        };
+        tokio::time::timeout(self.timeout, run)
+            .await
+            .map_err(|_| unavailable("timed out".to_string()))?
+    }
+}
+
+#[async_trait]
+impl RedisAdmin for RedisCommandAdmin {
+    async fn ping(&self, node: &CacheNode) -> DataResult<()> {
+        self.query::<String>(node, &redis::cmd("PING")).await.map(|_| ())
+    }
+
+    async fn replication_info(&self, node: &CacheNode) -> DataResult<ReplicationInfo> {
+        let info: String = self.query(node, redis::cmd("INFO").arg("replication")).await?;
+        ReplicationInfo::parse(&info)
+    }
+
+    async fn config_set(&self, node: &CacheNode, parameter: &str, value: &str) -> DataResult<()> {
+        self.query::<()>(node, redis::cmd("CONFIG").arg("SET").arg(parameter).arg(value))
+            .await
+    }
+
+    async fn config_rewrite(&self, node: &CacheNode) -> DataResult<()> {
+        self.query::<()>(node, redis::cmd("CONFIG").arg("REWRITE")).await
+    }
+
+    async fn kill_clients(&self, node: &CacheNode) -> DataResult<()> {
+        self.query::<i64>(node, redis::cmd("CLIENT").arg("KILL").arg("TYPE").arg("normal"))
+            .await
+            .map(|_| ())
+    }
+
+    async fn replicate_from(&self, node: &CacheNode, primary: Option<&CacheNode>) -> DataResult<()> {
+        let mut cmd = redis::cmd("REPLICAOF");
+        match primary {
+            Some(primary) => cmd.arg(&primary.address).arg(primary.port),
+            None => cmd.arg("NO").arg("ONE"),
+        };
+        self.query::<()>(node, &cmd).await
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FailoverStep {
+    pub at: DateTime<Utc>,
+    pub node_id: String,
+    pub action: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FailoverReport {
+    pub cluster_id: String,
+    pub shard_id: String,
+    pub old_primary: String,
+    pub new_primary: String,
+    pub promoted_offset: u64,
+    // False when the old primary was unreachable; it is demoted as soon as it answers again
+    pub old_primary_fenced: bool,
+    pub repointed: Vec<String>,
+    pub timeline: Vec<FailoverStep>,
+}
+
+#[derive(Default)]
+struct HealthState {
+    down_since: HashMap<(String, String), DateTime<Utc>>,
+    // Old primaries that missed fencing, keyed by node, mapped to the primary they must follow
+    pending_demotion: HashMap<(String, String), String>,
+}
+
+// Promotes the most caught-up replica of a shard after fencing the old primary, and optionally
+// does so automatically once a primary has been down for longer than a grace period
+pub struct FailoverOrchestrator {
+    redis: Arc<dyn RedisAdmin>,
+    nodes: RwLock<HashMap<String, Vec<CacheNode>>>,
+    health: Mutex<HealthState>,
+    in_flight: Mutex<HashSet<(String, String)>>,
+    automatic_grace: Option<chrono::Duration>,
+    events: broadcast::Sender<EventNotification>,
+}
+
+fn shard_of(node: &CacheNode) -> &str {
+    node.shard_id.as_deref().unwrap_or_default()
+}
+
+impl FailoverOrchestrator {
+    pub fn new(redis: Arc<dyn RedisAdmin>) -> Self {
+        let (events, _) = broadcast::channel(256);
+        Self {
+            redis,
+            nodes: RwLock::new(HashMap::new()),
+            health: Mutex::new(HealthState::default()),
+            in_flight: Mutex::new(HashSet::new()),
+            automatic_grace: None,
+            events,
+        }
+    }
+
+    // Fail a shard over on its own once its primary has been down for `grace`
+    pub fn with_automatic_failover(mut self, grace: chrono::Duration) -> Self {
+        self.automatic_grace = Some(grace);
+        self
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<EventNotification> {
+        self.events.subscribe()
+    }
+
+    pub async fn register_cluster(&self, cluster_id: &str, nodes: Vec<CacheNode>) {
+        self.nodes.write().await.insert(cluster_id.to_string(), nodes);
+    }
+
+    pub async fn list_nodes(&self, cluster_id: &str) -> DataResult<Vec<CacheNode>> {
+        self.nodes
+            .read()
+            .await
+            .get(cluster_id)
+            .cloned()
+            .ok_or_else(|| DataError::NotFound(format!("Cache cluster {} not found", cluster_id)))
+    }
+
+    fn notify(&self, cluster_id: &str, node_id: Option<&str>, event_type: EventType, severity: EventSeverity, message: String) {
+        let _ = self.events.send(EventNotification {
+            id: uuid::Uuid::new_v4().to_string(),
+            cluster_id: cluster_id.to_string(),
+            node_id: node_id.map(str::to_string),
+            event_type,
+            message,
+            severity,
+            timestamp: Utc::now(),
+        });
+    }
+
+    async fn fence(&self, node: &CacheNode) -> DataResult<()> {
+        self.redis.config_set(node, "min-replicas-to-write", FENCE_MIN_REPLICAS).await?;
+        self.redis.config_set(node, "min-replicas-max-lag", "1").await?;
+        // Persist so a restart does not bring it back writable
+        self.redis.config_rewrite(node).await?;
+        self.redis.kill_clients(node).await
+    }
+
+    pub async fn failover_shard(&self, cluster_id: &str, shard_id: &str) -> DataResult<FailoverReport> {
+        let key = (cluster_id.to_string(), shard_id.to_string());
+        if !self.in_flight.lock().await.insert(key.clone()) {
+            return Err(DataError::Conflict(format!(
+                "Failover of shard {} in {} is already running",
+                shard_id, cluster_id
+            )));
+        }
+        let result = self.run_failover(cluster_id, shard_id).await;
+        self.in_flight.lock().await.remove(&key);
+        if let Err(e) = &result {
+            error!("Failover of shard {} in {} failed: {}", shard_id, cluster_id, e);
+            self.notify(
+                cluster_id,
+                None,
+                EventType::Failure,
+                EventSeverity::Critical,
+                format!("Failover of shard {} failed: {}", shard_id, e),
+            );
+        }
+        result
+    }
+
+    async fn run_failover(&self, cluster_id: &str, shard_id: &str) -> DataResult<FailoverReport> {
+        let nodes: Vec<CacheNode> = self
+            .list_nodes(cluster_id)
+            .await?
+            .into_iter()
+            .filter(|n| shard_of(n) == shard_id)
+            .collect();
+        let old_primary = nodes
+            .iter()
+            .find(|n| n.role == NodeRole::Primary)
+            .cloned()
+            .ok_or_else(|| DataError::NotFound(format!("Shard {} in {} has no primary", shard_id, cluster_id)))?;
+        let mut timeline = Vec::new();
+        let mut step = |node: &CacheNode, action: String| {
+            timeline.push(FailoverStep {
+                at: Utc::now(),
+                node_id: node.id.clone(),
+                action,
+            })
+        };
+
+        let mut candidates = Vec::new();
+        for replica in nodes.iter().filter(|n| n.role == NodeRole::Replica) {
+            match self.redis.replication_info(replica).await {
+                Ok(info) => {
+                    step(replica, format!("replication offset {}", info.offset));
+                    candidates.push((replica.clone(), info.offset));
+                }
+                Err(e) => step(replica, format!("skipped: {}", e)),
+            }
+        }
+        // Highest offset wins; ties go to the lowest node id so repeated runs agree
+        let (promoted, promoted_offset) = candidates
+            .iter()
+            .max_by(|(a, a_offset), (b, b_offset)| a_offset.cmp(b_offset).then_with(|| b.id.cmp(&a.id)))
+            .cloned()
+            .ok_or_else(|| DataError::Unavailable(format!("Shard {} in {} has no reachable replica", shard_id, cluster_id)))?;
+
+        let fenced = match self.fence(&old_primary).await {
+            Ok(()) => {
+                step(&old_primary, "fenced: writes refused, config rewritten, clients killed".to_string());
+                true
+            }
+            Err(DataError::Unavailable(e)) => {
+                step(&old_primary, format!("unreachable, demotion deferred until it returns: {}", e));
+                false
+            }
+            // A reachable primary that refuses fencing could keep taking writes; do not promote
+            Err(e) => return Err(e),
+        };
+
+        self.redis.replicate_from(&promoted, None).await?;
+        step(&promoted, "promoted to primary".to_string());
+
+        let mut repointed = Vec::new();
+        for replica in nodes.iter().filter(|n| n.role == NodeRole::Replica && n.id != promoted.id) {
+            match self.redis.replicate_from(replica, Some(&promoted)).await {
+                Ok(()) => {
+                    step(replica, format!("replicating from {}", promoted.id));
+                    repointed.push(replica.id.clone());
+                }
+                Err(e) => step(replica, format!("repoint failed: {}", e)),
+            }
+        }
+        if fenced {
+            match self.redis.replicate_from(&old_primary, Some(&promoted)).await {
+                Ok(()) => step(&old_primary, format!("demoted, replicating from {}", promoted.id)),
+                Err(e) => step(&old_primary, format!("demotion failed: {}", e)),
+            }
+        }
+
+        {
+            let mut clusters = self.nodes.write().await;
+            for node in clusters.get_mut(cluster_id).into_iter().flatten() {
+                if node.id == promoted.id {
+                    node.role = NodeRole::Primary;
+                    node.status = NodeStatus::Available;
+                } else if node.id == old_primary.id {
+                    node.role = NodeRole::Replica;
+                    if !fenced {
+                        node.status = NodeStatus::Failed;
+                    }
+                }
+            }
+        }
+        {
+            let mut health = self.health.lock().await;
+            let node_key = (cluster_id.to_string(), old_primary.id.clone());
+            health.down_since.remove(&node_key);
+            if !fenced {
+                health.pending_demotion.insert(node_key, promoted.id.clone());
+            }
+        }
+
+        let summary = timeline
+            .iter()
+            .map(|s| format!("{} {}: {}", s.at.to_rfc3339(), s.node_id, s.action))
+            .collect::<Vec<_>>()
+            .join("; ");
+        info!(
+            "Failed over shard {} in {} from {} to {}",
+            shard_id, cluster_id, old_primary.id, promoted.id
+        );
+        self.notify(
+            cluster_id,
+            Some(&promoted.id),
+            EventType::Recovery,
+            EventSeverity::Warning,
+            format!(
+                "Shard {} failed over from {} to {}: {}",
+                shard_id, old_primary.id, promoted.id, summary
+            ),
+        );
+        Ok(FailoverReport {
+            cluster_id: cluster_id.to_string(),
+            shard_id: shard_id.to_string(),
+            old_primary: old_primary.id,
+            new_primary: promoted.id,
+            promoted_offset,
+            old_primary_fenced: fenced,
+            repointed,
+            timeline,
+        })
+    }
+
+    // Pings every node; primaries down past the grace period fail over when automatic mode is on,
+    // and old primaries that missed fencing are fenced and demoted when they come back
+    pub async fn check_health(&self, cluster_id: &str, now: DateTime<Utc>) -> DataResult<Vec<FailoverReport>> {
+        let nodes = self.list_nodes(cluster_id).await?;
+        let mut due = Vec::new();
+        for node in &nodes {
+            let node_key = (cluster_id.to_string(), node.id.clone());
+            let reachable = self.redis.ping(node).await.is_ok();
+            let status = if reachable {
+                self.health.lock().await.down_since.remove(&node_key);
+                let demote_to = self.health.lock().await.pending_demotion.get(&node_key).cloned();
+                match demote_to {
+                    Some(primary_id) => self.demote_returning(cluster_id, node, &primary_id, &nodes).await,
+                    None => NodeStatus::Available,
+                }
+            } else {
+                let down_since = *self.health.lock().await.down_since.entry(node_key).or_insert(now);
+                let shard_pending = due.contains(&shard_of(node).to_string());
+                if node.role == NodeRole::Primary
+                    && !shard_pending
+                    && self.automatic_grace.is_some_and(|grace| now - down_since >= grace)
+                {
+                    due.push(shard_of(node).to_string());
+                }
+                NodeStatus::Failed
+            };
+            let mut clusters = self.nodes.write().await;
+            if let Some(record) = clusters
+                .get_mut(cluster_id)
+                .and_then(|nodes| nodes.iter_mut().find(|n| n.id == node.id))
+            {
+                record.status = status;
+            }
+        }
+
+        let mut reports = Vec::new();
+        for shard_id in due {
+            warn!("Primary of shard {} in {} down past grace period, failing over", shard_id, cluster_id);
+            if let Ok(report) = self.failover_shard(cluster_id, &shard_id).await {
+                reports.push(report);
+            }
+        }
+        Ok(reports)
+    }
+
+    async fn demote_returning(&self, cluster_id: &str, node: &CacheNode, primary_id: &str, nodes: &[CacheNode]) -> NodeStatus {
+        let Some(primary) = nodes.iter().find(|n| n.id == primary_id) else {
+            return NodeStatus::Failed;
+        };
+        let result = match self.fence(node).await {
+            Ok(()) => self.redis.replicate_from(node, Some(primary)).await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) => {
+                self.health
+                    .lock()
+                    .await
+                    .pending_demotion
+                    .remove(&(cluster_id.to_string(), node.id.clone()));
+                info!("Demoted returning primary {} to replica of {}", node.id, primary_id);
+                self.notify(
+                    cluster_id,
+                    Some(&node.id),
+                    EventType::Recovery,
+                    EventSeverity::Info,
+                    format!("Former primary {} fenced and now replicating from {}", node.id, primary_id),
+                );
+                NodeStatus::Available
+            }
+            Err(e) => {
+                warn!("Failed to demote returning primary {}: {}", node.id, e);
+                NodeStatus::Failed
+            }
+        }
+    }
+
+    pub fn spawn_health_checker(self: Arc<Self>, cluster_id: String, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = self.check_health(&cluster_id, Utc::now()).await {
+                    warn!("Health check of cache cluster {} failed: {}", cluster_id, e);
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex as StdMutex;
+
+    #[derive(Default)]
+    struct MockRedis {
+        offsets: StdMutex<HashMap<String, u64>>,
+        down: StdMutex<HashSet<String>>,
+        log: StdMutex<Vec<String>>,
+    }
+
+    impl MockRedis {
+        fn reach(&self, node: &CacheNode) -> DataResult<()> {
+            if self.down.lock().unwrap().contains(&node.id) {
+                return Err(DataError::Unavailable(format!("{} refused connection", node.id)));
+            }
+            Ok(())
+        }
+
+        fn record(&self, node: &CacheNode, command: String) -> DataResult<()> {
+            self.reach(node)?;
+            self.log.lock().unwrap().push(format!("{} {}", node.id, command));
+            Ok(())
+        }
+
+        fn commands(&self) -> Vec<String> {
+            self.log.lock().unwrap().clone()
+        }
+    }
+
+    #[async_trait]
+    impl RedisAdmin for MockRedis {
+        async fn ping(&self, node: &CacheNode) -> DataResult<()> {
+            self.reach(node)
+        }
+
+        async fn replication_info(&self, node: &CacheNode) -> DataResult<ReplicationInfo> {
+            self.reach(node)?;
+            Ok(ReplicationInfo {
+                role: node.role,
+                offset: self.offsets.lock().unwrap()[&node.id],
+                master_link_up: false,
+            })
+        }
+
+        async fn config_set(&self, node: &CacheNode, parameter: &str, value: &str) -> DataResult<()> {
+            self.record(node, format!("CONFIG SET {} {}", parameter, value))
+        }
+
+        async fn config_rewrite(&self, node: &CacheNode) -> DataResult<()> {
+            self.record(node, "CONFIG REWRITE".to_string())
+        }
+
+        async fn kill_clients(&self, node: &CacheNode) -> DataResult<()> {
+            self.record(node, "CLIENT KILL TYPE normal".to_string())
+        }
+
+        async fn replicate_from(&self, node: &CacheNode, primary: Option<&CacheNode>) -> DataResult<()> {
+            let target = primary.map(|p| p.id.as_str()).unwrap_or("NO ONE");
+            self.record(node, format!("REPLICAOF {}", target))
+        }
+    }
+
+    fn node(id: &str, role: NodeRole) -> CacheNode {
+        CacheNode {
+            id: id.to_string(),
+            cluster_id: "sessions".to_string(),
+            status: NodeStatus::Available,
+            address: format!("{}.cache.internal", id),
+            port: 6379,
+            availability_zone: "us-east-1a".to_string(),
+            created_at: Utc::now(),
+            shard_id: Some("0001".to_string()),
+            role,
+        }
+    }
+
+    async fn setup(grace: Option<chrono::Duration>) -> (Arc<MockRedis>, FailoverOrchestrator) {
+        let redis = Arc::new(MockRedis::default());
+        redis.offsets.lock().unwrap().extend([
+            ("r1".to_string(), 1_000),
+            ("r2".to_string(), 1_450),
+            ("r3".to_string(), 1_200),
+        ]);
+        let mut orchestrator = FailoverOrchestrator::new(redis.clone());
+        if let Some(grace) = grace {
+            orchestrator = orchestrator.with_automatic_failover(grace);
+        }
+        orchestrator
+            .register_cluster(
+                "sessions",
+                vec![
+                    node("p1", NodeRole::Primary),
+                    node("r1", NodeRole::Replica),
+                    node("r2", NodeRole::Replica),
+                    node("r3", NodeRole::Replica),
+                ],
+            )
+            .await;
+        (redis, orchestrator)
+    }
+
+    #[test]
+    fn test_parse_replication_info() {
+        let info = "# Replication\r\nrole:slave\r\nmaster_host:10.0.0.1\r\nmaster_link_status:up\r\nslave_repl_offset:8812\r\n";
+        let parsed = ReplicationInfo::parse(info).unwrap();
+        assert_eq!(parsed.role, NodeRole::Replica);
+        assert_eq!(parsed.offset, 8812);
+        assert!(parsed.master_link_up);
+        assert!(ReplicationInfo::parse("role:sentinel\r\n").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_failover_promotes_highest_offset_after_fencing() {
+        let (redis, orchestrator) = setup(None).await;
+        let mut events = orchestrator.subscribe();
+
+        let report = orchestrator.failover_shard("sessions", "0001").await.unwrap();
+        assert_eq!(report.new_primary, "r2");
+        assert_eq!(report.promoted_offset, 1_450);
+        assert!(report.old_primary_fenced);
+        assert_eq!(report.repointed, vec!["r1".to_string(), "r3".to_string()]);
+
+        let commands = redis.commands();
+        let promote = commands.iter().position(|c| c == "r2 REPLICAOF NO ONE").unwrap();
+        let fence: Vec<_> = commands.iter().take(4).cloned().collect();
+        assert_eq!(
+            fence,
+            vec![
+                "p1 CONFIG SET min-replicas-to-write 1000000",
+                "p1 CONFIG SET min-replicas-max-lag 1",
+                "p1 CONFIG REWRITE",
+                "p1 CLIENT KILL TYPE normal",
+            ]
+        );
+        assert_eq!(promote, 4);
+        assert!(commands.contains(&"r1 REPLICAOF r2".to_string()));
+        assert!(commands.contains(&"p1 REPLICAOF r2".to_string()));
+
+        let nodes = orchestrator.list_nodes("sessions").await.unwrap();
+        let roles: HashMap<_, _> = nodes.iter().map(|n| (n.id.as_str(), n.role)).collect();
+        assert_eq!(roles["r2"], NodeRole::Primary);
+        assert_eq!(roles["p1"], NodeRole::Replica);
+        let event = events.recv().await.unwrap();
+        assert!(matches!(event.event_type, EventType::Recovery));
+        assert!(event.message.contains("promoted to primary"));
+    }
+
+    #[tokio::test]
+    async fn test_automatic_failover_after_grace_and_deferred_demotion() {
+        let grace = chrono::Duration::seconds(30);
+        let (redis, orchestrator) = setup(Some(grace)).await;
+        redis.down.lock().unwrap().insert("p1".to_string());
+        let start = Utc::now();
+
+        assert!(orchestrator.check_health("sessions", start).await.unwrap().is_empty());
+        assert!(orchestrator
+            .check_health("sessions", start + chrono::Duration::seconds(10))
+            .await
+            .unwrap()
+            .is_empty());
+        let reports = orchestrator.check_health("sessions", start + grace).await.unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].new_primary, "r2");
+        assert!(!reports[0].old_primary_fenced);
+        assert!(!redis.commands().iter().any(|c| c.starts_with("p1")));
+
+        // When the old primary answers again it is fenced before it rejoins as a replica
+        redis.down.lock().unwrap().clear();
+        orchestrator
+            .check_health("sessions", start + chrono::Duration::seconds(60))
+            .await
+            .unwrap();
+        let p1: Vec<_> = redis.commands().into_iter().filter(|c| c.starts_with("p1")).collect();
+        assert_eq!(p1.first().unwrap(), "p1 CONFIG SET min-replicas-to-write 1000000");
+        assert_eq!(p1.last().unwrap(), "p1 REPLICAOF r2");
+        let nodes = orchestrator.list_nodes("sessions").await.unwrap();
+        let p1 = nodes.iter().find(|n| n.id == "p1").unwrap();
+        assert_eq!(p1.role, NodeRole::Replica);
+        assert!(matches!(p1.status, NodeStatus::Available));
+    }
+}
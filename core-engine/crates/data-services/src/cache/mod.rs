@@ -5,6 +5,10 @@ use chrono::{DateTime, Utc};
 
 use crate::error::DataResult;
 
+pub mod failover;
+
+pub use failover::{FailoverOrchestrator, FailoverReport, RedisAdmin, RedisCommandAdmin};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CacheCluster {
     pub id: String,
@@ -57,6 +61,17 @@ pub struct CacheNode {
     pub port: u16,
     pub availability_zone: String,
     pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub shard_id: Option<String>,
+    #[serde(default)]
+    pub role: NodeRole,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NodeRole {
+    Primary,
+    #[default]
+    Replica,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -94,6 +109,8 @@ pub trait CacheManager: Send + Sync {
     async fn get_node(&self, cluster_id: &str, node_id: &str) -> DataResult<CacheNode>;
     async fn list_nodes(&self, cluster_id: &str) -> DataResult<Vec<CacheNode>>;
     async fn reboot_node(&self, cluster_id: &str, node_id: &str) -> DataResult<()>;
+    // Fences the shard's primary, then promotes the replica with the highest replication offset
+    async fn failover_shard(&self, cluster_id: &str, shard_id: &str) -> DataResult<FailoverReport>;
     async fn get_metrics(&self, cluster_id: &str, window: chrono::Duration) -> DataResult<Vec<CacheMetrics>>;
 }
 
@@ -0,0 +1,771 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgConnection;
+use sqlx::{Connection, Executor};
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+
+use crate::error::{DataError, DataResult};
+use super::credentials::{quote_identifier, DatabaseCredentials};
+use super::{BackupJob, BackupStatus, DatabaseInstance, DatabaseManager, InstanceStatus};
+
+pub const CLONE_OF_TAG: &str = "sirsi:clone-of";
+pub const CLONE_EXPIRES_AT_TAG: &str = "sirsi:clone-expires-at";
+pub const DEFAULT_CLONE_TTL_HOURS: i64 = 72;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CloneOptions {
+    pub name: Option<String>,
+    pub masking_script: Option<String>,
+    pub ttl_hours: i64,
+    // Storage-level snapshots are faster than a restore; set false to always restore a backup
+    pub prefer_snapshot: bool,
+    pub requested_by: Option<String>,
+    pub tags: HashMap<String, String>,
+}
+
+impl Default for CloneOptions {
+    fn default() -> Self {
+        Self {
+            name: None,
+            masking_script: None,
+            ttl_hours: DEFAULT_CLONE_TTL_HOURS,
+            prefer_snapshot: true,
+            requested_by: None,
+            tags: HashMap::new(),
+        }
+    }
+}
+
+// `expression` is SQL evaluated per row, e.g. `'user' || id || '@example.test'`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaskingRule {
+    pub table: String,
+    pub column: String,
+    pub expression: String,
+}
+
+// Registered transformations for PII columns; the version increases each time a script is
+// re-registered under the same name so clone lineage pins exactly what ran
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaskingScript {
+    pub name: String,
+    pub version: u32,
+    pub rules: Vec<MaskingRule>,
+    // Raw SQL run after the rules, for transformations a column rule cannot express
+    pub statements: Vec<String>,
+}
+
+impl MaskingScript {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            version: 0,
+            rules: Vec::new(),
+            statements: Vec::new(),
+        }
+    }
+
+    pub fn with_rule(mut self, table: impl Into<String>, column: impl Into<String>, expression: impl Into<String>) -> Self {
+        self.rules.push(MaskingRule {
+            table: table.into(),
+            column: column.into(),
+            expression: expression.into(),
+        });
+        self
+    }
+
+    pub fn with_statement(mut self, sql: impl Into<String>) -> Self {
+        self.statements.push(sql.into());
+        self
+    }
+
+    pub fn sql(&self) -> Vec<String> {
+        self.rules
+            .iter()
+            .map(|rule| {
+                let table = rule.table.split('.').map(quote_identifier).collect::<Vec<_>>().join(".");
+                format!("UPDATE {} SET {} = {}", table, quote_identifier(&rule.column), rule.expression)
+            })
+            .chain(self.statements.iter().cloned())
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CloneSource {
+    Backup { backup_id: String },
+    Snapshot { snapshot_id: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaskingLineage {
+    pub script: String,
+    pub version: u32,
+    pub rows_affected: u64,
+    pub applied_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CloneStatus {
+    Provisioning,
+    Masking,
+    Available,
+    Failed,
+    Deleted,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DatabaseClone {
+    pub clone_id: String,
+    pub source_id: String,
+    pub source: Option<CloneSource>,
+    pub masking: Option<MaskingLineage>,
+    pub status: CloneStatus,
+    pub error: Option<String>,
+    pub requested_by: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub expiry_warned_at: Option<DateTime<Utc>>,
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+#[async_trait]
+pub trait CloneStore: Send + Sync {
+    async fn save(&self, clone: &DatabaseClone) -> DataResult<()>;
+    async fn get(&self, clone_id: &str) -> DataResult<Option<DatabaseClone>>;
+    async fn list(&self) -> DataResult<Vec<DatabaseClone>>;
+}
+
+#[derive(Default)]
+pub struct InMemoryCloneStore {
+    clones: RwLock<HashMap<String, DatabaseClone>>,
+}
+
+impl InMemoryCloneStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl CloneStore for InMemoryCloneStore {
+    async fn save(&self, clone: &DatabaseClone) -> DataResult<()> {
+        self.clones.write().await.insert(clone.clone_id.clone(), clone.clone());
+        Ok(())
+    }
+
+    async fn get(&self, clone_id: &str) -> DataResult<Option<DatabaseClone>> {
+        Ok(self.clones.read().await.get(clone_id).cloned())
+    }
+
+    async fn list(&self) -> DataResult<Vec<DatabaseClone>> {
+        let mut clones: Vec<_> = self.clones.read().await.values().cloned().collect();
+        clones.sort_by_key(|c| c.created_at);
+        Ok(clones)
+    }
+}
+
+#[async_trait]
+pub trait BackupCatalog: Send + Sync {
+    async fn list_backups(&self, instance_id: &str) -> DataResult<Vec<BackupJob>>;
+}
+
+// Backends that can clone at the storage layer, e.g. copy-on-write volume snapshots
+#[async_trait]
+pub trait SnapshotCloner: Send + Sync {
+    fn supports(&self, source: &DatabaseInstance) -> bool;
+    // Returns the created instance and the id of the snapshot it was cut from
+    async fn clone_from_snapshot(
+        &self,
+        source: &DatabaseInstance,
+        clone: DatabaseInstance,
+    ) -> DataResult<(DatabaseInstance, String)>;
+}
+
+#[async_trait]
+pub trait MaskingExecutor: Send + Sync {
+    // Runs all statements atomically and returns the rows they touched
+    async fn execute(&self, instance: &DatabaseInstance, statements: &[String]) -> DataResult<u64>;
+}
+
+#[async_trait]
+pub trait CloneNotifier: Send + Sync {
+    async fn clone_expiring(&self, clone: &DatabaseClone) -> DataResult<()>;
+}
+
+pub struct PostgresMaskingExecutor {
+    admin: DatabaseCredentials,
+}
+
+impl PostgresMaskingExecutor {
+    pub fn new(admin: DatabaseCredentials) -> Self {
+        Self { admin }
+    }
+}
+
+#[async_trait]
+impl MaskingExecutor for PostgresMaskingExecutor {
+    async fn execute(&self, instance: &DatabaseInstance, statements: &[String]) -> DataResult<u64> {
+        let masking_error = |e: sqlx::Error| DataError::Database(format!("Masking {} failed: {}", instance.id, e));
+        let mut conn = PgConnection::connect_with(&self.admin.connect_options(instance)?)
+            .await
+            .map_err(|e| DataError::Database(format!("Failed to connect to {} as admin: {}", instance.id, e)))?;
+        let result = async {
+            let mut tx = conn.begin().await.map_err(masking_error)?;
+            let mut rows = 0;
+            for statement in statements {
+                rows += tx.execute(statement.as_str()).await.map_err(masking_error)?.rows_affected();
+            }
+            tx.commit().await.map_err(masking_error)?;
+            Ok(rows)
+        }
+        .await;
+        let _ = conn.close().await;
+        result
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReapReport {
+    pub warned: Vec<String>,
+    pub deleted: Vec<String>,
+}
+
+// Ephemeral copies of managed instances. A clone is only reported Available once masking has
+// committed; if masking fails the clone is deleted rather than left holding unmasked data.
+pub struct InstanceCloner {
+    instances: Arc<dyn DatabaseManager>,
+    backups: Arc<dyn BackupCatalog>,
+    store: Arc<dyn CloneStore>,
+    snapshots: Option<Arc<dyn SnapshotCloner>>,
+    masking: Option<Arc<dyn MaskingExecutor>>,
+    notifier: Option<Arc<dyn CloneNotifier>>,
+    scripts: RwLock<HashMap<String, MaskingScript>>,
+    warning_lead: chrono::Duration,
+}
+
+impl InstanceCloner {
+    pub fn new(instances: Arc<dyn DatabaseManager>, backups: Arc<dyn BackupCatalog>, store: Arc<dyn CloneStore>) -> Self {
+        Self {
+            instances,
+            backups,
+            store,
+            snapshots: None,
+            masking: None,
+            notifier: None,
+            scripts: RwLock::new(HashMap::new()),
+            warning_lead: chrono::Duration::days(1),
+        }
+    }
+
+    pub fn with_snapshots(mut self, snapshots: Arc<dyn SnapshotCloner>) -> Self {
+        self.snapshots = Some(snapshots);
+        self
+    }
+
+    pub fn with_masking_executor(mut self, masking: Arc<dyn MaskingExecutor>) -> Self {
+        self.masking = Some(masking);
+        self
+    }
+
+    pub fn with_notifier(mut self, notifier: Arc<dyn CloneNotifier>) -> Self {
+        self.notifier = Some(notifier);
+        self
+    }
+
+    pub fn with_warning_lead(mut self, lead: chrono::Duration) -> Self {
+        self.warning_lead = lead;
+        self
+    }
+
+    pub async fn register_masking_script(&self, mut script: MaskingScript) -> DataResult<MaskingScript> {
+        if script.rules.is_empty() && script.statements.is_empty() {
+            return Err(DataError::Validation(format!("Masking script {} has nothing to run", script.name)));
+        }
+        let mut scripts = self.scripts.write().await;
+        script.version = scripts.get(&script.name).map(|s| s.version + 1).unwrap_or(1);
+        scripts.insert(script.name.clone(), script.clone());
+        Ok(script)
+    }
+
+    pub async fn get_masking_script(&self, name: &str) -> DataResult<MaskingScript> {
+        self.scripts
+            .read()
+            .await
+            .get(name)
+            .cloned()
+            .ok_or_else(|| DataError::NotFound(format!("Masking script {} not found", name)))
+    }
+
+    async fn latest_backup(&self, source_id: &str) -> DataResult<BackupJob> {
+        self.backups
+            .list_backups(source_id)
+            .await?
+            .into_iter()
+            .filter(|b| matches!(b.status, BackupStatus::Completed))
+            .max_by_key(|b| b.completed_at.unwrap_or(b.started_at))
+            .ok_or_else(|| DataError::NotFound(format!("Instance {} has no completed backup to clone from", source_id)))
+    }
+
+    async fn provision(
+        &self,
+        source: &DatabaseInstance,
+        config: DatabaseInstance,
+        options: &CloneOptions,
+    ) -> DataResult<(DatabaseInstance, CloneSource)> {
+        if let Some(snapshots) = self.snapshots.as_ref().filter(|s| options.prefer_snapshot && s.supports(source)) {
+            let (instance, snapshot_id) = snapshots.clone_from_snapshot(source, config).await?;
+            return Ok((instance, CloneSource::Snapshot { snapshot_id }));
+        }
+        let backup = self.latest_backup(&source.id).await?;
+        let created = self.instances.create_instance(config).await?;
+        let restored = self.instances.restore_backup(&backup.id, &created.id).await?;
+        Ok((restored, CloneSource::Backup { backup_id: backup.id }))
+    }
+
+    pub async fn clone_instance(&self, source_id: &str, options: CloneOptions) -> DataResult<DatabaseClone> {
+        if options.ttl_hours <= 0 {
+            return Err(DataError::Validation("Clone TTL must be positive".into()));
+        }
+        let script = match &options.masking_script {
+            Some(name) => {
+                if self.masking.is_none() {
+                    return Err(DataError::Config(format!(
+                        "Masking script {} requested but no masking executor is configured",
+                        name
+                    )));
+                }
+                Some(self.get_masking_script(name).await?)
+            }
+            None => None,
+        };
+        let source = self.instances.get_instance(source_id).await?;
+
+        let now = Utc::now();
+        let expires_at = now + chrono::Duration::hours(options.ttl_hours);
+        let clone_id = format!("{}-clone-{}", source.id, &uuid::Uuid::new_v4().simple().to_string()[..8]);
+        let mut config = source.clone();
+        config.id = clone_id.clone();
+        config.name = options.name.clone().unwrap_or_else(|| clone_id.clone());
+        config.status = InstanceStatus::Creating;
+        config.created_at = now;
+        config.updated_at = now;
+        config.tags.extend(options.tags.clone());
+        config.tags.insert(CLONE_OF_TAG.to_string(), source.id.clone());
+        config.tags.insert(CLONE_EXPIRES_AT_TAG.to_string(), expires_at.to_rfc3339());
+
+        let mut record = DatabaseClone {
+            clone_id: clone_id.clone(),
+            source_id: source.id.clone(),
+            source: None,
+            masking: None,
+            status: CloneStatus::Provisioning,
+            error: None,
+            requested_by: options.requested_by.clone(),
+            created_at: now,
+            expires_at,
+            expiry_warned_at: None,
+            deleted_at: None,
+        };
+        self.store.save(&record).await?;
+
+        let instance = match self.provision(&source, config, &options).await {
+            Ok((instance, lineage)) => {
+                record.source = Some(lineage);
+                instance
+            }
+            Err(e) => {
+                record.status = CloneStatus::Failed;
+                record.error = Some(e.to_string());
+                self.store.save(&record).await?;
+                return Err(e);
+            }
+        };
+
+        if let (Some(script), Some(masking)) = (&script, &self.masking) {
+            record.status = CloneStatus::Masking;
+            self.store.save(&record).await?;
+            match masking.execute(&instance, &script.sql()).await {
+                Ok(rows_affected) => {
+                    record.masking = Some(MaskingLineage {
+                        script: script.name.clone(),
+                        version: script.version,
+                        rows_affected,
+                        applied_at: Utc::now(),
+                    });
+                }
+                Err(e) => {
+                    error!("Masking clone {} of {} failed, deleting it: {}", clone_id, source.id, e);
+                    record.error = Some(e.to_string());
+                    record.status = match self.instances.delete_instance(&clone_id).await {
+                        Ok(()) => {
+                            record.deleted_at = Some(Utc::now());
+                            CloneStatus::Deleted
+                        }
+                        Err(delete_error) => {
+                            error!("Failed to delete unmasked clone {}: {}", clone_id, delete_error);
+                            CloneStatus::Failed
+                        }
+                    };
+                    self.store.save(&record).await?;
+                    return Err(e);
+                }
+            }
+        }
+
+        record.status = CloneStatus::Available;
+        self.store.save(&record).await?;
+        info!("Cloned {} to {} ({:?}), expires at {}", source.id, clone_id, record.source, expires_at);
+        Ok(record)
+    }
+
+    pub async fn get_clone(&self, clone_id: &str) -> DataResult<DatabaseClone> {
+        self.store
+            .get(clone_id)
+            .await?
+            .ok_or_else(|| DataError::NotFound(format!("Clone {} not found", clone_id)))
+    }
+
+    pub async fn list_clones(&self, source_id: Option<&str>) -> DataResult<Vec<DatabaseClone>> {
+        let clones = self.store.list().await?;
+        Ok(clones
+            .into_iter()
+            .filter(|c| source_id.is_none_or(|id| c.source_id == id))
+            .collect())
+    }
+
+    // Deletes clones past their TTL and warns once about those expiring within the warning lead.
+    // A failed delete is retried on the next pass.
+    pub async fn reap_expired(&self, now: DateTime<Utc>) -> DataResult<ReapReport> {
+        let mut report = ReapReport::default();
+        for mut clone in self.store.list().await? {
+            if clone.status == CloneStatus::Deleted {
+                continue;
+            }
+            if clone.expires_at <= now {
+                match self.instances.delete_instance(&clone.clone_id).await {
+                    Ok(()) | Err(DataError::NotFound(_)) => {
+                        clone.status = CloneStatus::Deleted;
+                        clone.deleted_at = Some(now);
+                        self.store.save(&clone).await?;
+                        info!("Deleted expired clone {} of {}", clone.clone_id, clone.source_id);
+                        report.deleted.push(clone.clone_id);
+                    }
+                    Err(e) => warn!("Failed to delete expired clone {}: {}", clone.clone_id, e),
+                }
+            } else if clone.expires_at - now <= self.warning_lead && clone.expiry_warned_at.is_none() {
+                if let Some(notifier) = &self.notifier {
+                    if let Err(e) = notifier.clone_expiring(&clone).await {
+                        warn!("Failed to send expiry warning for clone {}: {}", clone.clone_id, e);
+                        continue;
+                    }
+                }
+                warn!("Clone {} of {} expires at {}", clone.clone_id, clone.source_id, clone.expires_at);
+                clone.expiry_warned_at = Some(now);
+                self.store.save(&clone).await?;
+                report.warned.push(clone.clone_id);
+            }
+        }
+        Ok(report)
+    }
+
+    pub fn spawn_reaper(self: Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = self.reap_expired(Utc::now()).await {
+                    warn!("Clone reaper pass failed: {}", e);
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::credentials::CredentialRotation;
+    use crate::database::testing::{instance, postgres_from_env};
+    use crate::database::{BackupType, DatabaseMetrics, EngineDetails};
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Fleet {
+        instances: Mutex<HashMap<String, DatabaseInstance>>,
+        restores: Mutex<Vec<(String, String)>>,
+        deleted: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl DatabaseManager for Fleet {
+        async fn create_instance(&self, config: DatabaseInstance) -> DataResult<DatabaseInstance> {
+            self.instances.lock().unwrap().insert(config.id.clone(), config.clone());
+            Ok(config)
+        }
+
+        async fn modify_instance(&self, instance: DatabaseInstance) -> DataResult<DatabaseInstance> {
+            self.create_instance(instance).await
+        }
+
+        async fn delete_instance(&self, id: &str) -> DataResult<()> {
+            self.deleted.lock().unwrap().push(id.to_string());
+            self.instances
+                .lock()
+                .unwrap()
+                .remove(id)
+                .map(|_| ())
+                .ok_or_else(|| DataError::NotFound(id.to_string()))
+        }
+
+        async fn get_instance(&self, id: &str) -> DataResult<DatabaseInstance> {
+            self.instances
+                .lock()
+                .unwrap()
+                .get(id)
+                .cloned()
+                .ok_or_else(|| DataError::NotFound(id.to_string()))
+        }
+
+        async fn list_instances(&self) -> DataResult<Vec<DatabaseInstance>> {
+            Ok(self.instances.lock().unwrap().values().cloned().collect())
+        }
+
+        async fn start_instance(&self, _id: &str) -> DataResult<()> {
+            Ok(())
+        }
+
+        async fn stop_instance(&self, _id: &str) -> DataResult<()> {
+            Ok(())
+        }
+
+        async fn restart_instance(&self, _id: &str) -> DataResult<()> {
+            Ok(())
+        }
+
+        async fn create_backup(&self, _instance_id: &str) -> DataResult<BackupJob> {
+            Err(DataError::Internal("not supported".to_string()))
+        }
+
+        async fn restore_backup(&self, backup_id: &str, target_instance_id: &str) -> DataResult<DatabaseInstance> {
+            self.restores
+                .lock()
+                .unwrap()
+                .push((backup_id.to_string(), target_instance_id.to_string()));
+            let mut target = self.get_instance(target_instance_id).await?;
+            target.status = InstanceStatus::Available;
+            self.create_instance(target).await
+        }
+
+        async fn get_metrics(&self, _instance_id: &str, _window: chrono::Duration) -> DataResult<Vec<DatabaseMetrics>> {
+            Ok(Vec::new())
+        }
+
+        async fn rotate_credentials(&self, _instance_id: &str) -> DataResult<CredentialRotation> {
+            Err(DataError::Internal("not supported".to_string()))
+        }
+
+        async fn clone_instance(&self, _source_id: &str, _options: CloneOptions) -> DataResult<DatabaseClone> {
+            Err(DataError::Internal("not supported".to_string()))
+        }
+
+        async fn get_engine_details(&self, _instance_id: &str) -> DataResult<EngineDetails> {
+            Err(DataError::Internal("not supported".to_string()))
+        }
+    }
+
+    struct Backups;
+
+    #[async_trait]
+    impl BackupCatalog for Backups {
+        async fn list_backups(&self, instance_id: &str) -> DataResult<Vec<BackupJob>> {
+            let backup = |id: &str, hours_ago: i64, status: BackupStatus| BackupJob {
+                id: id.to_string(),
+                instance_id: instance_id.to_string(),
+                status,
+                type_: BackupType::Automated,
+                started_at: Utc::now() - chrono::Duration::hours(hours_ago),
+                completed_at: Some(Utc::now() - chrono::Duration::hours(hours_ago)),
+                size_bytes: 1024,
+                storage_location: format!("s3://backups/{}", id),
+                manifest: None,
+            };
+            Ok(vec![
+                backup("bk-old", 48, BackupStatus::Completed),
+                backup("bk-latest", 2, BackupStatus::Completed),
+                backup("bk-running", 0, BackupStatus::InProgress),
+            ])
+        }
+    }
+
+    #[derive(Default)]
+    struct Recorded {
+        statements: Mutex<Vec<String>>,
+        warned: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl MaskingExecutor for Recorded {
+        async fn execute(&self, _instance: &DatabaseInstance, statements: &[String]) -> DataResult<u64> {
+            self.statements.lock().unwrap().extend(statements.iter().cloned());
+            Ok(statements.len() as u64)
+        }
+    }
+
+    #[async_trait]
+    impl CloneNotifier for Recorded {
+        async fn clone_expiring(&self, clone: &DatabaseClone) -> DataResult<()> {
+            self.warned.lock().unwrap().push(clone.clone_id.clone());
+            Ok(())
+        }
+    }
+
+    async fn cloner(fleet: Arc<Fleet>, recorded: Arc<Recorded>) -> InstanceCloner {
+        fleet.create_instance(instance("localhost", 5432)).await.unwrap();
+        InstanceCloner::new(fleet, Arc::new(Backups), Arc::new(InMemoryCloneStore::new()))
+            .with_masking_executor(recorded.clone())
+            .with_notifier(recorded)
+    }
+
+    #[tokio::test]
+    async fn test_clone_lineage_and_reaper_only_deletes_expired() {
+        let fleet = Arc::new(Fleet::default());
+        let recorded = Arc::new(Recorded::default());
+        let cloner = cloner(fleet.clone(), recorded.clone()).await;
+        let v1 = cloner
+            .register_masking_script(MaskingScript::new("pii").with_rule("public.users", "email", "'redacted'"))
+            .await
+            .unwrap();
+        let v2 = cloner
+            .register_masking_script(
+                MaskingScript::new("pii")
+                    .with_rule("public.users", "email", "'user' || id || '@example.test'")
+                    .with_statement("TRUNCATE audit_log"),
+            )
+            .await
+            .unwrap();
+        assert_eq!((v1.version, v2.version), (1, 2));
+
+        let options = |ttl_hours| CloneOptions {
+            masking_script: Some("pii".into()),
+            ttl_hours,
+            requested_by: Some("pr-1234".into()),
+            ..Default::default()
+        };
+        let clone = cloner.clone_instance("db-orders", options(72)).await.unwrap();
+        assert_eq!(clone.status, CloneStatus::Available);
+        assert_eq!(clone.source, Some(CloneSource::Backup { backup_id: "bk-latest".into() }));
+        let masking = clone.masking.as_ref().unwrap();
+        assert_eq!((masking.script.as_str(), masking.version, masking.rows_affected), ("pii", 2, 2));
+        assert_eq!(
+            *recorded.statements.lock().unwrap(),
+            [
+                "UPDATE \"public\".\"users\" SET \"email\" = 'user' || id || '@example.test'".to_string(),
+                "TRUNCATE audit_log".to_string(),
+            ]
+        );
+        assert_eq!(*fleet.restores.lock().unwrap(), [("bk-latest".to_string(), clone.clone_id.clone())]);
+        let instance = fleet.get_instance(&clone.clone_id).await.unwrap();
+        assert_eq!(instance.tags[CLONE_OF_TAG], "db-orders");
+        assert_eq!(cloner.get_clone(&clone.clone_id).await.unwrap().masking.unwrap().version, 2);
+
+        let expiring = cloner.clone_instance("db-orders", options(12)).await.unwrap();
+        let expired = cloner.clone_instance("db-orders", options(1)).await.unwrap();
+        assert_eq!(cloner.list_clones(Some("db-orders")).await.unwrap().len(), 3);
+        assert!(cloner.list_clones(Some("db-other")).await.unwrap().is_empty());
+
+        let later = Utc::now() + chrono::Duration::hours(2);
+        let report = cloner.reap_expired(later).await.unwrap();
+        assert_eq!(report.deleted, [expired.clone_id.as_str()]);
+        assert_eq!(report.warned, [expiring.clone_id.as_str()]);
+        assert_eq!(*fleet.deleted.lock().unwrap(), [expired.clone_id.as_str()]);
+        assert_eq!(cloner.get_clone(&expired.clone_id).await.unwrap().status, CloneStatus::Deleted);
+        assert!(fleet.get_instance(&clone.clone_id).await.is_ok());
+
+        // The warning goes out once
+        let report = cloner.reap_expired(later).await.unwrap();
+        assert!(report.warned.is_empty() && report.deleted.is_empty());
+        assert_eq!(recorded.warned.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_postgres_masking_runs_before_clone_is_available() {
+        let Some((source, credentials)) = postgres_from_env() else {
+            eprintln!("SIRSI_TEST_POSTGRES_URL not set, skipping");
+            return;
+        };
+        // The restore lands on the same server, so the clone sees the seeded table
+        let table = format!("clone_users_{}", &uuid::Uuid::new_v4().simple().to_string()[..8]);
+        let mut conn = PgConnection::connect_with(&credentials.connect_options(&source).unwrap())
+            .await
+            .unwrap();
+        sqlx::raw_sql(&format!(
+            "CREATE TABLE {0} (id INT PRIMARY KEY, email TEXT, ssn TEXT); \
+             INSERT INTO {0} VALUES (1, 'ada@corp.com', '123-45-6789'), (2, 'alan@corp.com', '987-65-4321')",
+            table
+        ))
+        .execute(&mut conn)
+        .await
+        .unwrap();
+
+        let fleet = Arc::new(Fleet::default());
+        fleet.create_instance(source.clone()).await.unwrap();
+        let cloner = InstanceCloner::new(fleet, Arc::new(Backups), Arc::new(InMemoryCloneStore::new()))
+            .with_masking_executor(Arc::new(PostgresMaskingExecutor::new(credentials.clone())));
+        cloner
+            .register_masking_script(
+                MaskingScript::new("pii")
+                    .with_rule(&table, "email", "'user' || id || '@example.test'")
+                    .with_rule(&table, "ssn", "NULL"),
+            )
+            .await
+            .unwrap();
+        let clone = cloner
+            .clone_instance(
+                &source.id,
+                CloneOptions {
+                    masking_script: Some("pii".into()),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(clone.masking.unwrap().rows_affected, 4);
+
+        let rows: Vec<(String, Option<String>)> = sqlx::query_as(&format!("SELECT email, ssn FROM {} ORDER BY id", table))
+            .fetch_all(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!(
+            rows,
+            [("user1@example.test".to_string(), None), ("user2@example.test".to_string(), None)]
+        );
+
+        // A failing script never leaves an unmasked clone behind
+        cloner
+            .register_masking_script(MaskingScript::new("broken").with_rule("no_such_table", "email", "NULL"))
+            .await
+            .unwrap();
+        let failed = cloner
+            .clone_instance(
+                &source.id,
+                CloneOptions {
+                    masking_script: Some("broken".into()),
+                    ..Default::default()
+                },
+            )
+            .await;
+        assert!(matches!(failed, Err(DataError::Database(_))));
+        let clones = cloner.list_clones(None).await.unwrap();
+        assert_eq!(clones.last().unwrap().status, CloneStatus::Deleted);
+
+        sqlx::raw_sql(&format!("DROP TABLE {}", table)).execute(&mut conn).await.unwrap();
+    }
+}
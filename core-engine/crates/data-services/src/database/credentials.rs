@@ -0,0 +1,703 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rand::rngs::OsRng;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sirsi_key_vault::secret::{RotationPolicy, Secret, SecretManager, SecretValue};
+use sirsi_key_vault::{Credential, KeyVaultError};
+use sqlx::postgres::{PgConnectOptions, PgConnection};
+use sqlx::{Connection, Executor};
+use tokio::sync::{broadcast, Mutex, RwLock};
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+
+use crate::error::{DataError, DataResult};
+use super::{DatabaseEngine, DatabaseInstance};
+
+// Postgres keeps one password per role, so an overlap window needs two login roles that take
+// turns: the standby gets the new password while apps still hold the active one
+pub const STANDBY_ROLE_SUFFIX: &str = "_clone";
+const PASSWORD_ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+const PASSWORD_LENGTH: usize = 32;
+
+const USERNAME_METADATA: &str = "username";
+const INSTANCE_METADATA: &str = "instance_id";
+const PREVIOUS_USERNAME_METADATA: &str = "previous_username";
+const PREVIOUS_EXPIRES_METADATA: &str = "previous_expires_at";
+
+pub fn generate_password() -> String {
+    let mut rng = OsRng;
+    (0..PASSWORD_LENGTH)
+        .map(|_| PASSWORD_ALPHABET[rng.gen_range(0..PASSWORD_ALPHABET.len())] as char)
+        .collect()
+}
+
+// Role administration on a running instance
+#[async_trait]
+pub trait RoleAdmin: Send + Sync {
+    // Creates a login role with no password; `member_of` grants it another role's privileges
+    async fn create_login_role(&self, instance: &DatabaseInstance, username: &str, member_of: Option<&str>) -> DataResult<()>;
+    // None clears the password so the role can no longer authenticate
+    async fn set_password(&self, instance: &DatabaseInstance, username: &str, password: Option<&str>) -> DataResult<()>;
+    async fn verify_login(&self, instance: &DatabaseInstance, username: &str, password: &str) -> DataResult<()>;
+}
+
+pub(crate) fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+// Login for a database on a managed instance. The password is usually a key-vault reference
+// (`vault:<secret id>`) and has to be resolved before connecting.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DatabaseCredentials {
+    pub username: String,
+    pub password: Credential,
+    pub database: String,
+}
+
+impl std::fmt::Debug for DatabaseCredentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DatabaseCredentials")
+            .field("username", &self.username)
+            .field("database", &self.database)
+            .finish_non_exhaustive()
+    }
+}
+
+impl DatabaseCredentials {
+    pub fn new(username: impl Into<String>, password: impl Into<String>, database: impl Into<String>) -> Self {
+        Self {
+            username: username.into(),
+            password: Credential::plaintext(password),
+            database: database.into(),
+        }
+    }
+
+    pub fn from_vault(username: impl Into<String>, secret_id: impl Into<String>, database: impl Into<String>) -> Self {
+        Self {
+            username: username.into(),
+            password: Credential::vault(secret_id),
+            database: database.into(),
+        }
+    }
+
+    // A copy holding the current password, fetched fresh so rotations are picked up
+    pub async fn resolve(&self, secrets: &dyn SecretManager) -> DataResult<Self> {
+        let secret_id = self.password.reference().map(|r| r.secret_id.clone()).unwrap_or_default();
+        let password = self
+            .password
+            .resolve(Some(secrets))
+            .await
+            .map_err(|e| vault_error(&secret_id, e))?;
+        Ok(Self::new(&self.username, password.expose(), &self.database))
+    }
+
+    pub fn connect_options(&self, instance: &DatabaseInstance) -> DataResult<PgConnectOptions> {
+        let Credential::Plaintext(password) = &self.password else {
+            return Err(DataError::Config(format!(
+                "Credentials for {} on {} must be resolved before connecting",
+                self.username, instance.id
+            )));
+        };
+        Ok(PgConnectOptions::new()
+            .host(&instance.endpoint)
+            .port(instance.port)
+            .username(&self.username)
+            .password(password.expose())
+            .database(&self.database))
+    }
+}
+
+pub struct PostgresRoleAdmin {
+    admin: DatabaseCredentials,
+}
+
+impl PostgresRoleAdmin {
+    pub fn new(admin: DatabaseCredentials) -> Self {
+        Self { admin }
+    }
+
+    async fn execute(&self, instance: &DatabaseInstance, statement: &str) -> DataResult<()> {
+        let mut conn = PgConnection::connect_with(&self.admin.connect_options(instance)?)
+            .await
+            .map_err(|e| DataError::Database(format!("Failed to connect to {} as admin: {}", instance.id, e)))?;
+        let result = conn
+            .execute(statement)
+            .await
+            .map(|_| ())
+            .map_err(|e| DataError::Database(format!("Role change on {} failed: {}", instance.id, e)));
+        let _ = conn.close().await;
+        result
+    }
+}
+
+#[async_trait]
+impl RoleAdmin for PostgresRoleAdmin {
+    async fn create_login_role(&self, instance: &DatabaseInstance, username: &str, member_of: Option<&str>) -> DataResult<()> {
+        let mut statement = format!("CREATE ROLE {} WITH LOGIN PASSWORD NULL", quote_identifier(username));
+        if let Some(role) = member_of {
+            statement.push_str(&format!(" IN ROLE {}", quote_identifier(role)));
+        }
+        self.execute(instance, &statement).await
+    }
+
+    async fn set_password(&self, instance: &DatabaseInstance, username: &str, password: Option<&str>) -> DataResult<()> {
+        let password = password.map(quote_literal).unwrap_or_else(|| "NULL".to_string());
+        self.execute(
+            instance,
+            &format!("ALTER ROLE {} WITH PASSWORD {}", quote_identifier(username), password),
+        )
+        .await
+    }
+
+    async fn verify_login(&self, instance: &DatabaseInstance, username: &str, password: &str) -> DataResult<()> {
+        let login = DatabaseCredentials::new(username, password, &self.admin.database);
+        let mut conn = PgConnection::connect_with(&login.connect_options(instance)?)
+            .await
+            .map_err(|e| DataError::Auth(format!("Login as {} on {} failed: {}", username, instance.id, e)))?;
+        let result = conn
+            .execute("SELECT 1")
+            .await
+            .map(|_| ())
+            .map_err(|e| DataError::Auth(format!("Query as {} on {} failed: {}", username, instance.id, e)));
+        let _ = conn.close().await;
+        result
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppCredentialConfig {
+    pub username: String,
+    pub secret_id: String,
+    pub rotation_policy: RotationPolicy,
+    // How long the previous credential keeps working after a rotation
+    pub overlap: chrono::Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RotationStep {
+    SetPassword,
+    Verify,
+    UpdateSecret,
+    ExpireOld,
+}
+
+// Carries usernames and secret versions only, never passwords
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum CredentialEvent {
+    Rotated(CredentialRotation),
+    Expired {
+        instance_id: String,
+        username: String,
+    },
+    Failed {
+        instance_id: String,
+        step: RotationStep,
+        error: String,
+        rolled_back: bool,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CredentialRotation {
+    pub instance_id: String,
+    pub secret_id: String,
+    pub username: String,
+    pub previous_username: String,
+    pub old_version: i32,
+    pub new_version: i32,
+    pub previous_expires_at: DateTime<Utc>,
+}
+
+struct ManagedCredential {
+    instance: DatabaseInstance,
+    config: AppCredentialConfig,
+}
+
+struct PendingExpiry {
+    instance_id: String,
+    username: String,
+    expires_at: DateTime<Utc>,
+}
+
+fn vault_error(secret_id: &str, e: KeyVaultError) -> DataError {
+    match e {
+        KeyVaultError::NotFound(msg) => DataError::NotFound(msg),
+        e => DataError::Provider(format!("Key vault operation on {} failed: {}", secret_id, e)),
+    }
+}
+
+fn standby_of(config: &AppCredentialConfig, active: &str) -> String {
+    if active == config.username {
+        format!("{}{}", config.username, STANDBY_ROLE_SUFFIX)
+    } else {
+        config.username.clone()
+    }
+}
+
+// Provisions application users whose passwords live in key-vault and rotates them in place:
+// set on the standby role, verify, publish the new secret version, then expire the old role
+// once the overlap window has passed
+pub struct CredentialRotator {
+    secrets: Arc<dyn SecretManager>,
+    admin: Arc<dyn RoleAdmin>,
+    managed: RwLock<HashMap<String, ManagedCredential>>,
+    pending: Mutex<Vec<PendingExpiry>>,
+    rotation: Mutex<()>,
+    events: broadcast::Sender<CredentialEvent>,
+}
+
+impl CredentialRotator {
+    pub fn new(secrets: Arc<dyn SecretManager>, admin: Arc<dyn RoleAdmin>) -> Self {
+        let (events, _) = broadcast::channel(256);
+        Self {
+            secrets,
+            admin,
+            managed: RwLock::new(HashMap::new()),
+            pending: Mutex::new(Vec::new()),
+            rotation: Mutex::new(()),
+            events,
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<CredentialEvent> {
+        self.events.subscribe()
+    }
+
+    fn alert(&self, instance_id: &str, step: RotationStep, e: &DataError, rolled_back: bool) {
+        error!(
+            "Credential rotation for {} failed at {:?} (rolled back: {}): {}",
+            instance_id, step, rolled_back, e
+        );
+        let _ = self.events.send(CredentialEvent::Failed {
+            instance_id: instance_id.to_string(),
+            step,
+            error: e.to_string(),
+            rolled_back,
+        });
+    }
+
+    pub async fn provision(&self, instance: DatabaseInstance, config: AppCredentialConfig) -> DataResult<Secret> {
+        if !matches!(instance.engine, DatabaseEngine::PostgreSQL) {
+            return Err(DataError::Validation(format!(
+                "Credential rotation is not supported for {:?} instance {}",
+                instance.engine, instance.id
+            )));
+        }
+        if self.managed.read().await.contains_key(&instance.id) {
+            return Err(DataError::Conflict(format!("Instance {} already has a managed app user", instance.id)));
+        }
+
+        let standby = standby_of(&config, &config.username);
+        self.admin.create_login_role(&instance, &config.username, None).await?;
+        self.admin.create_login_role(&instance, &standby, Some(&config.username)).await?;
+        let password = generate_password();
+        self.admin.set_password(&instance, &config.username, Some(&password)).await?;
+        self.admin.verify_login(&instance, &config.username, &password).await?;
+
+        let now = Utc::now();
+        let secret = Secret {
+            id: config.secret_id.clone(),
+            name: format!("{} app credentials", instance.name),
+            description: Some(format!("Application login for database instance {}", instance.id)),
+            value: SecretValue::Plain(password),
+            version: 0,
+            created_at: now,
+            updated_at: now,
+            expires_at: None,
+            metadata: HashMap::from([
+                (USERNAME_METADATA.to_string(), config.username.clone()),
+                (INSTANCE_METADATA.to_string(), instance.id.clone()),
+            ]),
+            labels: HashMap::new(),
+            rotation_policy: Some(config.rotation_policy.clone()),
+        };
+        let secret = match self.secrets.create_secret(secret).await {
+            Ok(secret) => secret,
+            Err(e) => {
+                let _ = self.admin.set_password(&instance, &config.username, None).await;
+                return Err(vault_error(&config.secret_id, e));
+            }
+        };
+        info!("Provisioned app user {} on {}", config.username, instance.id);
+        self.managed
+            .write()
+            .await
+            .insert(instance.id.clone(), ManagedCredential { instance, config });
+        Ok(secret)
+    }
+
+    pub async fn rotate_credentials(&self, instance_id: &str) -> DataResult<CredentialRotation> {
+        let _rotation = self.rotation.lock().await;
+        let (instance, config) = {
+            let managed = self.managed.read().await;
+            let credential = managed
+                .get(instance_id)
+                .ok_or_else(|| DataError::NotFound(format!("Instance {} has no managed app user", instance_id)))?;
+            (credential.instance.clone(), credential.config.clone())
+        };
+        // The standby role is still serving the overlap window of the previous rotation
+        if let Some(pending) = self.pending.lock().await.iter().find(|p| p.instance_id == instance_id) {
+            return Err(DataError::Conflict(format!(
+                "Previous credentials for {} stay valid until {}",
+                instance_id, pending.expires_at
+            )));
+        }
+
+        let current = self
+            .secrets
+            .get_secret(&config.secret_id)
+            .await
+            .map_err(|e| vault_error(&config.secret_id, e))?;
+        let active = current
+            .metadata
+            .get(USERNAME_METADATA)
+            .cloned()
+            .unwrap_or_else(|| config.username.clone());
+        let standby = standby_of(&config, &active);
+        let password = generate_password();
+
+        if let Err(e) = self.admin.set_password(&instance, &standby, Some(&password)).await {
+            let rolled_back = self.admin.set_password(&instance, &standby, None).await.is_ok();
+            self.alert(instance_id, RotationStep::SetPassword, &e, rolled_back);
+            return Err(e);
+        }
+        if let Err(e) = self.admin.verify_login(&instance, &standby, &password).await {
+            let rolled_back = self.admin.set_password(&instance, &standby, None).await.is_ok();
+            self.alert(instance_id, RotationStep::Verify, &e, rolled_back);
+            return Err(e);
+        }
+
+        let previous_expires_at = Utc::now() + config.overlap;
+        let mut next = current.clone();
+        next.value = SecretValue::Plain(password);
+        next.metadata.insert(USERNAME_METADATA.to_string(), standby.clone());
+        next.metadata.insert(PREVIOUS_USERNAME_METADATA.to_string(), active.clone());
+        next.metadata
+            .insert(PREVIOUS_EXPIRES_METADATA.to_string(), previous_expires_at.to_rfc3339());
+        let updated = match self.secrets.update_secret(next).await {
+            Ok(updated) => updated,
+            Err(e) => {
+                let e = vault_error(&config.secret_id, e);
+                let rolled_back = self.admin.set_password(&instance, &standby, None).await.is_ok();
+                self.alert(instance_id, RotationStep::UpdateSecret, &e, rolled_back);
+                return Err(e);
+            }
+        };
+
+        self.pending.lock().await.push(PendingExpiry {
+            instance_id: instance_id.to_string(),
+            username: active.clone(),
+            expires_at: previous_expires_at,
+        });
+        let rotation = CredentialRotation {
+            instance_id: instance_id.to_string(),
+            secret_id: config.secret_id.clone(),
+            username: standby,
+            previous_username: active,
+            old_version: current.version,
+            new_version: updated.version,
+            previous_expires_at,
+        };
+        info!(
+            "Rotated credentials for {} to {} (secret version {})",
+            instance_id, rotation.username, rotation.new_version
+        );
+        let _ = self.events.send(CredentialEvent::Rotated(rotation.clone()));
+        Ok(rotation)
+    }
+
+    // Clears the password of every previous role whose overlap window ended by `now`; failures
+    // stay pending and are retried on the next call
+    pub async fn expire_due(&self, now: DateTime<Utc>) -> Vec<String> {
+        let due: Vec<PendingExpiry> = {
+            let mut pending = self.pending.lock().await;
+            let (due, waiting) = std::mem::take(&mut *pending).into_iter().partition(|p| p.expires_at <= now);
+            *pending = waiting;
+            due
+        };
+        let mut expired = Vec::new();
+        for entry in due {
+            let instance = self
+                .managed
+                .read()
+                .await
+                .get(&entry.instance_id)
+                .map(|c| c.instance.clone());
+            let result = match instance {
+                Some(instance) => self.admin.set_password(&instance, &entry.username, None).await,
+                None => Ok(()),
+            };
+            match result {
+                Ok(()) => {
+                    info!("Expired previous credentials {} on {}", entry.username, entry.instance_id);
+                    let _ = self.events.send(CredentialEvent::Expired {
+                        instance_id: entry.instance_id.clone(),
+                        username: entry.username.clone(),
+                    });
+                    expired.push(entry.username);
+                }
+                Err(e) => {
+                    self.alert(&entry.instance_id, RotationStep::ExpireOld, &e, false);
+                    self.pending.lock().await.push(entry);
+                }
+            }
+        }
+        expired
+    }
+
+    pub async fn rotate_due(&self, now: DateTime<Utc>) -> Vec<CredentialRotation> {
+        let candidates: Vec<(String, String)> = self
+            .managed
+            .read()
+            .await
+            .iter()
+            .filter(|(_, c)| c.config.rotation_policy.auto_rotate)
+            .map(|(id, c)| (id.clone(), c.config.secret_id.clone()))
+            .collect();
+        let mut rotated = Vec::new();
+        for (instance_id, secret_id) in candidates {
+            let due = match self.secrets.get_secret(&secret_id).await {
+                Ok(secret) => secret
+                    .rotation_policy
+                    .is_some_and(|policy| now - secret.updated_at >= policy.interval),
+                Err(e) => {
+                    warn!("Failed to read credentials secret {}: {}", secret_id, e);
+                    false
+                }
+            };
+            if !due {
+                continue;
+            }
+            // Failures have already alerted; the next pass retries
+            if let Ok(rotation) = self.rotate_credentials(&instance_id).await {
+                rotated.push(rotation);
+            }
+        }
+        rotated
+    }
+
+    pub fn spawn_scheduler(self: Arc<Self>, check_interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(check_interval);
+            loop {
+                ticker.tick().await;
+                let now = Utc::now();
+                self.expire_due(now).await;
+                self.rotate_due(now).await;
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::testing::{instance, postgres_from_env};
+    use sirsi_key_vault::secret::{InMemorySecretManager, RotationAlgorithm};
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    fn config(username: &str) -> AppCredentialConfig {
+        AppCredentialConfig {
+            username: username.to_string(),
+            secret_id: format!("db/{}/app", username),
+            rotation_policy: RotationPolicy {
+                interval: chrono::Duration::days(30),
+                algorithm: RotationAlgorithm::AES256,
+                auto_rotate: true,
+                notify_before: None,
+            },
+            overlap: chrono::Duration::minutes(10),
+        }
+    }
+
+    fn password(secret: &Secret) -> String {
+        match &secret.value {
+            SecretValue::Plain(password) => password.clone(),
+            other => panic!("unexpected secret value {:?}", other),
+        }
+    }
+
+    // Roles on a pretend instance; verification can be made to fail on demand
+    #[derive(Default)]
+    struct FakeRoles {
+        passwords: std::sync::Mutex<HashMap<String, Option<String>>>,
+        reject_verify: AtomicBool,
+    }
+
+    impl FakeRoles {
+        fn can_login(&self, username: &str, password: &str) -> bool {
+            self.passwords.lock().unwrap().get(username).cloned().flatten().as_deref() == Some(password)
+        }
+    }
+
+    #[async_trait]
+    impl RoleAdmin for FakeRoles {
+        async fn create_login_role(&self, _instance: &DatabaseInstance, username: &str, _member_of: Option<&str>) -> DataResult<()> {
+            self.passwords.lock().unwrap().insert(username.to_string(), None);
+            Ok(())
+        }
+
+        async fn set_password(&self, _instance: &DatabaseInstance, username: &str, password: Option<&str>) -> DataResult<()> {
+            self.passwords
+                .lock()
+                .unwrap()
+                .insert(username.to_string(), password.map(str::to_string));
+            Ok(())
+        }
+
+        async fn verify_login(&self, _instance: &DatabaseInstance, username: &str, password: &str) -> DataResult<()> {
+            if self.reject_verify.load(Ordering::SeqCst) || !self.can_login(username, password) {
+                return Err(DataError::Auth(format!("Login as {} failed", username)));
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rotation_overlap_and_rollback() {
+        let secrets = Arc::new(InMemorySecretManager::new());
+        let roles = Arc::new(FakeRoles::default());
+        let rotator = CredentialRotator::new(secrets.clone(), roles.clone());
+        let mut events = rotator.subscribe();
+
+        let initial = rotator.provision(instance("localhost", 5432), config("orders_app")).await.unwrap();
+        let first = password(&initial);
+        assert!(roles.can_login("orders_app", &first));
+
+        let rotation = rotator.rotate_credentials("db-orders").await.unwrap();
+        assert_eq!(rotation.username, "orders_app_clone");
+        assert_eq!((rotation.old_version, rotation.new_version), (1, 2));
+        let current = secrets.get_secret("db/orders_app/app").await.unwrap();
+        assert_eq!(current.metadata["username"], "orders_app_clone");
+        // Both credentials work during the overlap window
+        assert!(roles.can_login("orders_app", &first));
+        assert!(roles.can_login("orders_app_clone", &password(&current)));
+        assert!(matches!(rotator.rotate_credentials("db-orders").await, Err(DataError::Conflict(_))));
+
+        assert!(rotator.expire_due(Utc::now()).await.is_empty());
+        let expired = rotator.expire_due(rotation.previous_expires_at).await;
+        assert_eq!(expired, vec!["orders_app".to_string()]);
+        assert!(!roles.can_login("orders_app", &first));
+        assert!(matches!(events.recv().await.unwrap(), CredentialEvent::Rotated(_)));
+        assert!(matches!(events.recv().await.unwrap(), CredentialEvent::Expired { .. }));
+
+        roles.reject_verify.store(true, Ordering::SeqCst);
+        assert!(rotator.rotate_credentials("db-orders").await.is_err());
+        match events.recv().await.unwrap() {
+            CredentialEvent::Failed { step, rolled_back, .. } => {
+                assert_eq!(step, RotationStep::Verify);
+                assert!(rolled_back);
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+        // The standby role is locked again and the published secret is untouched
+        assert_eq!(roles.passwords.lock().unwrap()["orders_app"], None);
+        assert_eq!(secrets.get_secret("db/orders_app/app").await.unwrap().version, 2);
+        assert!(roles.can_login("orders_app_clone", &password(&current)));
+    }
+
+    #[tokio::test]
+    async fn test_vault_backed_credentials_never_expose_the_password() {
+        let secrets = InMemorySecretManager::new();
+        let mut password = Credential::plaintext("s3cr3t-orders");
+        password.ingest(&secrets, "db/orders/admin").await.unwrap();
+        let stored = DatabaseCredentials::from_vault("admin", "db/orders/admin", "orders");
+        let target = instance("localhost", 5432);
+
+        let written = serde_json::to_string(&stored).unwrap();
+        assert!(written.contains("vault:db/orders/admin"));
+        assert!(matches!(stored.connect_options(&target), Err(DataError::Config(_))));
+
+        let resolved = stored.resolve(&secrets).await.unwrap();
+        assert!(resolved.connect_options(&target).is_ok());
+        assert!(!format!("{:?}", resolved).contains("s3cr3t"));
+        assert!(serde_json::to_string(&resolved).is_err());
+        assert!(matches!(
+            DatabaseCredentials::from_vault("admin", "db/missing", "orders").resolve(&secrets).await,
+            Err(DataError::NotFound(_))
+        ));
+    }
+
+    // Verifies with a corrupted password, so the real server rejects the login
+    struct BrokenVerify(Arc<PostgresRoleAdmin>);
+
+    #[async_trait]
+    impl RoleAdmin for BrokenVerify {
+        async fn create_login_role(&self, instance: &DatabaseInstance, username: &str, member_of: Option<&str>) -> DataResult<()> {
+            self.0.create_login_role(instance, username, member_of).await
+        }
+
+        async fn set_password(&self, instance: &DatabaseInstance, username: &str, password: Option<&str>) -> DataResult<()> {
+            self.0.set_password(instance, username, password).await
+        }
+
+        async fn verify_login(&self, instance: &DatabaseInstance, username: &str, password: &str) -> DataResult<()> {
+            self.0.verify_login(instance, username, &format!("{}x", password)).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_postgres_rotation() {
+        let Some((instance, credentials)) = postgres_from_env() else {
+            eprintln!("SIRSI_TEST_POSTGRES_URL not set, skipping");
+            return;
+        };
+        let admin = Arc::new(PostgresRoleAdmin::new(credentials.clone()));
+        let username = format!("app_{}", &uuid::Uuid::new_v4().simple().to_string()[..8]);
+        let secrets = Arc::new(InMemorySecretManager::new());
+        let rotator = CredentialRotator::new(secrets.clone(), admin.clone());
+
+        let initial = rotator.provision(instance.clone(), config(&username)).await.unwrap();
+        let first = password(&initial);
+        admin.verify_login(&instance, &username, &first).await.unwrap();
+
+        let rotation = rotator.rotate_credentials(&instance.id).await.unwrap();
+        let second = password(&secrets.get_secret(&rotation.secret_id).await.unwrap());
+        admin.verify_login(&instance, &rotation.username, &second).await.unwrap();
+        admin.verify_login(&instance, &username, &first).await.unwrap();
+
+        rotator.expire_due(rotation.previous_expires_at).await;
+        assert!(admin.verify_login(&instance, &username, &first).await.is_err());
+        admin.verify_login(&instance, &rotation.username, &second).await.unwrap();
+
+        // A failed verification leaves the standby role unable to log in and the secret unchanged
+        let broken = CredentialRotator::new(secrets.clone(), Arc::new(BrokenVerify(admin.clone())));
+        broken.managed.write().await.insert(
+            instance.id.clone(),
+            ManagedCredential {
+                instance: instance.clone(),
+                config: config(&username),
+            },
+        );
+        assert!(matches!(broken.rotate_credentials(&instance.id).await, Err(DataError::Auth(_))));
+        assert_eq!(secrets.get_secret(&rotation.secret_id).await.unwrap().version, 2);
+        admin.verify_login(&instance, &rotation.username, &second).await.unwrap();
+        let mut conn = PgConnection::connect_with(&credentials.connect_options(&instance).unwrap()).await.unwrap();
+        let (locked,): (bool,) = sqlx::query_as("SELECT rolpassword IS NULL FROM pg_authid WHERE rolname = $1")
+            .bind(&username)
+            .fetch_one(&mut conn)
+            .await
+            .unwrap();
+        assert!(locked);
+
+        conn.execute(
+            format!(
+                "DROP ROLE {}; DROP ROLE {}",
+                quote_identifier(&rotation.username),
+                quote_identifier(&username)
+            )
+            .as_str(),
+        )
+        .await
+        .unwrap();
+    }
+}
@@ -0,0 +1,271 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Datelike, Utc};
+use sirsi_common::Retryable;
+use sirsi_observability::monitoring::{SilenceManager, SilenceMatcher};
+use sirsi_observability::ObservabilityError;
+use tracing::{info, warn};
+
+use crate::error::{DataError, DataResult};
+use super::{DatabaseManager, MaintenanceManager, MaintenanceTask, MaintenanceWindow};
+
+// Alerts about a database carry its id under this metadata key
+pub const INSTANCE_LABEL: &str = "instance_id";
+const SILENCE_CREATOR: &str = "data-services";
+
+fn observability_error(error: ObservabilityError) -> DataError {
+    DataError::from_kind(error.kind(), error.to_string())
+}
+
+impl MaintenanceWindow {
+    pub fn duration(&self) -> chrono::Duration {
+        chrono::Duration::hours(i64::from(self.duration_hours))
+    }
+
+    // The window occurrence in progress at `now`, or the next one to start
+    pub fn next_occurrence(&self, now: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
+        let days_ahead = (i64::from(self.day.num_days_from_monday()) - i64::from(now.weekday().num_days_from_monday()))
+            .rem_euclid(7);
+        let start = (now.date_naive() + chrono::Duration::days(days_ahead)).and_time(self.start_time).and_utc();
+        // This week's occurrence either hasn't ended yet or it's next week's that counts
+        let previous = start - chrono::Duration::weeks(1);
+        if previous + self.duration() > now {
+            return (previous, previous + self.duration());
+        }
+        if start + self.duration() <= now {
+            let next = start + chrono::Duration::weeks(1);
+            return (next, next + self.duration());
+        }
+        (start, start + self.duration())
+    }
+}
+
+// Wraps a MaintenanceManager so tasks scheduled with `silence_alerts` also silence the
+// instance's alerts for the length of its maintenance window
+pub struct SilencingMaintenanceManager {
+    inner: Arc<dyn MaintenanceManager>,
+    instances: Arc<dyn DatabaseManager>,
+    silences: Arc<SilenceManager>,
+}
+
+impl SilencingMaintenanceManager {
+    pub fn new(inner: Arc<dyn MaintenanceManager>, instances: Arc<dyn DatabaseManager>, silences: Arc<SilenceManager>) -> Self {
+        Self { inner, instances, silences }
+    }
+}
+
+#[async_trait]
+impl MaintenanceManager for SilencingMaintenanceManager {
+    async fn schedule_maintenance(&self, mut task: MaintenanceTask) -> DataResult<MaintenanceTask> {
+        if !task.silence_alerts {
+            return self.inner.schedule_maintenance(task).await;
+        }
+        let instance = self.instances.get_instance(&task.instance_id).await?;
+        let silence = self
+            .silences
+            .create_silence(
+                SilenceMatcher::label(INSTANCE_LABEL, instance.id.clone()),
+                task.scheduled_at,
+                task.scheduled_at + instance.maintenance_window.duration(),
+                format!("{:?} maintenance on {}: {}", task.task_type, instance.name, task.description),
+                SILENCE_CREATOR,
+            )
+            .await
+            .map_err(observability_error)?;
+        task.silence_id = Some(silence.id.clone());
+        match self.inner.schedule_maintenance(task).await {
+            Ok(task) => {
+                info!("Silenced alerts for {} until {} for maintenance {}", instance.id, silence.end, task.id);
+                Ok(task)
+            }
+            Err(e) => {
+                // Don't leave a silence behind for maintenance that never got scheduled
+                if let Err(cleanup) = self.silences.delete_silence(&silence.id).await {
+                    warn!("Failed to remove silence {}: {}", silence.id, cleanup);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    async fn get_maintenance_task(&self, id: &str) -> DataResult<MaintenanceTask> {
+        self.inner.get_maintenance_task(id).await
+    }
+
+    async fn list_maintenance_tasks(&self, instance_id: &str) -> DataResult<Vec<MaintenanceTask>> {
+        self.inner.list_maintenance_tasks(instance_id).await
+    }
+
+    async fn cancel_maintenance_task(&self, id: &str) -> DataResult<()> {
+        let task = self.inner.get_maintenance_task(id).await?;
+        self.inner.cancel_maintenance_task(id).await?;
+        if let Some(silence_id) = &task.silence_id {
+            match self.silences.delete_silence(silence_id).await {
+                Ok(()) | Err(ObservabilityError::NotFound(_)) => {}
+                Err(e) => return Err(observability_error(e)),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::testing::instance;
+    use crate::database::cloning::{CloneOptions, DatabaseClone};
+    use crate::database::credentials::CredentialRotation;
+    use crate::database::{
+        BackupJob, DatabaseInstance, DatabaseMetrics, EngineDetails, MaintenanceStatus, MaintenanceType,
+    };
+    use chrono::{NaiveTime, TimeZone, Weekday};
+    use sirsi_observability::monitoring::{AlertEvent, AlertSeverity, AlertState};
+    use std::collections::HashMap;
+    use tokio::sync::Mutex;
+
+    struct Instances(DatabaseInstance);
+
+    #[async_trait]
+    impl DatabaseManager for Instances {
+        async fn create_instance(&self, config: DatabaseInstance) -> DataResult<DatabaseInstance> {
+            Ok(config)
+        }
+
+        async fn modify_instance(&self, instance: DatabaseInstance) -> DataResult<DatabaseInstance> {
+            Ok(instance)
+        }
+
+        async fn delete_instance(&self, _id: &str) -> DataResult<()> {
+            Ok(())
+        }
+
+        async fn get_instance(&self, id: &str) -> DataResult<DatabaseInstance> {
+            if id == self.0.id {
+                Ok(self.0.clone())
+            } else {
+                Err(DataError::NotFound(id.to_string()))
+            }
+        }
+
+        async fn list_instances(&self) -> DataResult<Vec<DatabaseInstance>> {
+            Ok(vec![self.0.clone()])
+        }
+
+        async fn start_instance(&self, _id: &str) -> DataResult<()> {
+            Ok(())
+        }
+
+        async fn stop_instance(&self, _id: &str) -> DataResult<()> {
+            Ok(())
+        }
+
+        async fn restart_instance(&self, _id: &str) -> DataResult<()> {
+            Ok(())
+        }
+
+        async fn create_backup(&self, _instance_id: &str) -> DataResult<BackupJob> {
+            Err(DataError::Internal("not supported".to_string()))
+        }
+
+        async fn restore_backup(&self, _backup_id: &str, _target_instance_id: &str) -> DataResult<DatabaseInstance> {
+            Err(DataError::Internal("not supported".to_string()))
+        }
+
+        async fn get_metrics(&self, _instance_id: &str, _window: chrono::Duration) -> DataResult<Vec<DatabaseMetrics>> {
+            Ok(Vec::new())
+        }
+
+        async fn rotate_credentials(&self, _instance_id: &str) -> DataResult<CredentialRotation> {
+            Err(DataError::Internal("not supported".to_string()))
+        }
+
+        async fn clone_instance(&self, _source_id: &str, _options: CloneOptions) -> DataResult<DatabaseClone> {
+            Err(DataError::Internal("not supported".to_string()))
+        }
+
+        async fn get_engine_details(&self, _instance_id: &str) -> DataResult<EngineDetails> {
+            Err(DataError::Internal("not supported".to_string()))
+        }
+    }
+
+    #[derive(Default)]
+    struct Tasks(Mutex<HashMap<String, MaintenanceTask>>);
+
+    #[async_trait]
+    impl MaintenanceManager for Tasks {
+        async fn schedule_maintenance(&self, task: MaintenanceTask) -> DataResult<MaintenanceTask> {
+            self.0.lock().await.insert(task.id.clone(), task.clone());
+            Ok(task)
+        }
+
+        async fn get_maintenance_task(&self, id: &str) -> DataResult<MaintenanceTask> {
+            self.0.lock().await.get(id).cloned().ok_or_else(|| DataError::NotFound(id.to_string()))
+        }
+
+        async fn list_maintenance_tasks(&self, instance_id: &str) -> DataResult<Vec<MaintenanceTask>> {
+            Ok(self.0.lock().await.values().filter(|t| t.instance_id == instance_id).cloned().collect())
+        }
+
+        async fn cancel_maintenance_task(&self, id: &str) -> DataResult<()> {
+            self.0.lock().await.remove(id);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scheduled_maintenance_silences_instance_alerts() {
+        let mut db = instance("localhost", 5432);
+        db.maintenance_window = MaintenanceWindow {
+            day: Weekday::Sun,
+            start_time: NaiveTime::from_hms_opt(2, 0, 0).unwrap(),
+            duration_hours: 2,
+        };
+        // Friday: the next window is Sunday 02:00-04:00, and it stays "next" while in progress
+        let friday = Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap();
+        let (start, end) = db.maintenance_window.next_occurrence(friday);
+        assert_eq!(start, Utc.with_ymd_and_hms(2024, 3, 3, 2, 0, 0).unwrap());
+        assert_eq!(db.maintenance_window.next_occurrence(start + chrono::Duration::hours(1)), (start, end));
+        assert_eq!(db.maintenance_window.next_occurrence(end).0, start + chrono::Duration::weeks(1));
+
+        let silences = Arc::new(SilenceManager::new());
+        let manager =
+            SilencingMaintenanceManager::new(Arc::new(Tasks::default()), Arc::new(Instances(db.clone())), silences.clone());
+        let task = MaintenanceTask {
+            id: "mt-1".to_string(),
+            instance_id: db.id.clone(),
+            task_type: MaintenanceType::MinorUpgrade,
+            status: MaintenanceStatus::Scheduled,
+            scheduled_at: start,
+            started_at: None,
+            completed_at: None,
+            description: "15.5 to 15.6".to_string(),
+            silence_alerts: true,
+            silence_id: None,
+            operation: None,
+            depends_on: Vec::new(),
+            timeout_seconds: None,
+            error: None,
+        };
+        let task = manager.schedule_maintenance(task).await.unwrap();
+        let silence = silences.get_silence(task.silence_id.as_deref().unwrap()).await.unwrap();
+        assert_eq!((silence.start, silence.end), (start, end));
+
+        let alert = AlertEvent {
+            id: "a-1".to_string(),
+            rule_id: "db-cpu".to_string(),
+            severity: AlertSeverity::Critical,
+            state: AlertState::Firing,
+            message: "CPU above 90%".to_string(),
+            value: 95.0,
+            timestamp: start,
+            resolved_at: None,
+            metadata: HashMap::from([(INSTANCE_LABEL.to_string(), db.id.clone())]),
+            actions: Vec::new(),
+        };
+        assert_eq!(silences.matching(&alert, start + chrono::Duration::minutes(30)).await.len(), 1);
+
+        manager.cancel_maintenance_task("mt-1").await.unwrap();
+        assert!(silences.list_silences(start, true).await.is_empty());
+    }
+}
@@ -0,0 +1,554 @@
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::postgres::PgConnection;
+use sqlx::{Connection, Row};
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+use crate::error::{DataError, DataResult};
+use super::credentials::{quote_identifier, DatabaseCredentials};
+use super::{DatabaseEngine, DatabaseInstance};
+
+pub const DEFAULT_TRACKING_TABLE: &str = "sirsi_schema_migrations";
+// A migration containing this line runs outside a transaction
+pub const NO_TRANSACTION_DIRECTIVE: &str = "-- sirsi:no-transaction";
+
+pub fn checksum(sql: &str) -> String {
+    hex::encode(Sha256::digest(sql.as_bytes()))
+}
+
+// An uploaded file; the checksum is the client's sha256 of `sql` and guards against truncated uploads
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MigrationFile {
+    pub name: String,
+    pub sql: String,
+    pub checksum: String,
+}
+
+impl MigrationFile {
+    pub fn new(name: impl Into<String>, sql: impl Into<String>) -> Self {
+        let sql = sql.into();
+        Self {
+            name: name.into(),
+            checksum: checksum(&sql),
+            sql,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Migration {
+    pub version: i64,
+    pub name: String,
+    pub sql: String,
+    pub checksum: String,
+    pub transactional: bool,
+}
+
+impl Migration {
+    // Versions come from the file name: `0003_add_index.sql` or `V3__add_index.sql`
+    fn from_file(file: MigrationFile) -> DataResult<Self> {
+        let stem = file.name.strip_prefix(['V', 'v']).unwrap_or(&file.name);
+        let digits: String = stem.chars().take_while(|c| c.is_ascii_digit()).collect();
+        let version = digits
+            .parse::<i64>()
+            .map_err(|_| DataError::Validation(format!("Migration {} has no leading version number", file.name)))?;
+        if checksum(&file.sql) != file.checksum.to_ascii_lowercase() {
+            return Err(DataError::Validation(format!(
+                "Migration {} does not match its checksum",
+                file.name
+            )));
+        }
+        let upper = file.sql.to_ascii_uppercase();
+        // Postgres refuses these inside a transaction block
+        let transactional = !file.sql.lines().any(|l| l.trim() == NO_TRANSACTION_DIRECTIVE)
+            && !upper.contains(" CONCURRENTLY ")
+            && !upper.contains("ALTER SYSTEM")
+            && !upper.trim_start().starts_with("VACUUM");
+        Ok(Self {
+            version,
+            name: file.name,
+            checksum: file.checksum.to_ascii_lowercase(),
+            sql: file.sql,
+            transactional,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MigrationSet {
+    pub id: String,
+    pub migrations: Vec<Migration>,
+    pub uploaded_at: DateTime<Utc>,
+}
+
+impl MigrationSet {
+    pub fn new(id: impl Into<String>, files: Vec<MigrationFile>) -> DataResult<Self> {
+        let mut migrations = files
+            .into_iter()
+            .map(Migration::from_file)
+            .collect::<DataResult<Vec<_>>>()?;
+        migrations.sort_by_key(|m| m.version);
+        if let Some(pair) = migrations.windows(2).find(|pair| pair[0].version == pair[1].version) {
+            return Err(DataError::Validation(format!(
+                "Migrations {} and {} share version {}",
+                pair[0].name, pair[1].name, pair[0].version
+            )));
+        }
+        Ok(Self {
+            id: id.into(),
+            migrations,
+            uploaded_at: Utc::now(),
+        })
+    }
+
+    // Migrations not yet applied, in order. Fails if any applied file was edited afterwards.
+    pub fn pending(&self, applied: &HashMap<i64, String>) -> DataResult<Vec<&Migration>> {
+        let drifted: Vec<&str> = self
+            .migrations
+            .iter()
+            .filter(|m| applied.get(&m.version).is_some_and(|sum| *sum != m.checksum))
+            .map(|m| m.name.as_str())
+            .collect();
+        if !drifted.is_empty() {
+            return Err(DataError::Conflict(format!(
+                "Applied migrations changed since they ran: {}",
+                drifted.join(", ")
+            )));
+        }
+        let known: HashSet<i64> = self.migrations.iter().map(|m| m.version).collect();
+        let unknown = applied.keys().filter(|v| !known.contains(v)).count();
+        if unknown > 0 {
+            warn!("Migration set {} is missing {} applied versions", self.id, unknown);
+        }
+        Ok(self
+            .migrations
+            .iter()
+            .filter(|m| !applied.contains_key(&m.version))
+            .collect())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlannedMigration {
+    pub version: i64,
+    pub name: String,
+    pub transactional: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MigrationPlan {
+    pub instance_id: String,
+    pub migration_set_id: String,
+    pub applied_versions: Vec<i64>,
+    pub pending: Vec<PlannedMigration>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppliedMigration {
+    pub version: i64,
+    pub name: String,
+    pub transactional: bool,
+    pub duration_ms: u64,
+    pub rows_affected: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MigrationReport {
+    pub instance_id: String,
+    pub migration_set_id: String,
+    pub already_applied: usize,
+    pub applied: Vec<AppliedMigration>,
+}
+
+struct MigrationTarget {
+    instance: DatabaseInstance,
+    credentials: DatabaseCredentials,
+}
+
+fn db_error(instance_id: &str, e: sqlx::Error) -> DataError {
+    DataError::Database(format!("Migration on {} failed: {}", instance_id, e))
+}
+
+// Runs uploaded SQL migrations against managed Postgres instances, tracking applied versions in
+// a table on the instance and holding an advisory lock for the whole apply
+pub struct MigrationRunner {
+    sets: RwLock<HashMap<String, MigrationSet>>,
+    targets: RwLock<HashMap<String, MigrationTarget>>,
+    tracking_table: String,
+}
+
+impl Default for MigrationRunner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MigrationRunner {
+    pub fn new() -> Self {
+        Self {
+            sets: RwLock::new(HashMap::new()),
+            targets: RwLock::new(HashMap::new()),
+            tracking_table: DEFAULT_TRACKING_TABLE.to_string(),
+        }
+    }
+
+    // Optionally schema-qualified, e.g. `ops.schema_migrations`
+    pub fn with_tracking_table(mut self, table: impl Into<String>) -> DataResult<Self> {
+        let table = table.into();
+        let valid = table
+            .split('.')
+            .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'));
+        if !valid || table.split('.').count() > 2 {
+            return Err(DataError::Validation(format!("Invalid tracking table name {}", table)));
+        }
+        self.tracking_table = table;
+        Ok(self)
+    }
+
+    fn quoted_table(&self) -> String {
+        self.tracking_table
+            .split('.')
+            .map(quote_identifier)
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+
+    // Advisory lock key shared by every runner using the same tracking table
+    fn lock_key(&self) -> i64 {
+        let digest = Sha256::digest(self.tracking_table.as_bytes());
+        i64::from_be_bytes(digest[..8].try_into().expect("sha256 digest is 32 bytes"))
+    }
+
+    pub async fn register_instance(&self, instance: DatabaseInstance, credentials: DatabaseCredentials) -> DataResult<()> {
+        if !matches!(instance.engine, DatabaseEngine::PostgreSQL) {
+            return Err(DataError::Validation(format!(
+                "Migrations are not supported for {:?} instance {}",
+                instance.engine, instance.id
+            )));
+        }
+        self.targets
+            .write()
+            .await
+            .insert(instance.id.clone(), MigrationTarget { instance, credentials });
+        Ok(())
+    }
+
+    pub async fn upload_migration_set(&self, id: &str, files: Vec<MigrationFile>) -> DataResult<MigrationSet> {
+        let set = MigrationSet::new(id, files)?;
+        let mut sets = self.sets.write().await;
+        if sets.contains_key(id) {
+            return Err(DataError::Conflict(format!("Migration set {} already exists", id)));
+        }
+        sets.insert(id.to_string(), set.clone());
+        Ok(set)
+    }
+
+    pub async fn get_migration_set(&self, id: &str) -> DataResult<MigrationSet> {
+        self.sets
+            .read()
+            .await
+            .get(id)
+            .cloned()
+            .ok_or_else(|| DataError::NotFound(format!("Migration set {} not found", id)))
+    }
+
+    async fn connect(&self, instance_id: &str) -> DataResult<PgConnection> {
+        let targets = self.targets.read().await;
+        let target = targets
+            .get(instance_id)
+            .ok_or_else(|| DataError::NotFound(format!("Instance {} is not registered for migrations", instance_id)))?;
+        PgConnection::connect_with(&target.credentials.connect_options(&target.instance)?)
+            .await
+            .map_err(|e| db_error(instance_id, e))
+    }
+
+    async fn applied_versions(&self, conn: &mut PgConnection, instance_id: &str) -> DataResult<HashMap<i64, String>> {
+        let rows = sqlx::query(&format!("SELECT version, checksum FROM {}", self.quoted_table()))
+            .fetch_all(&mut *conn)
+            .await
+            .map_err(|e| db_error(instance_id, e))?;
+        rows.iter()
+            .map(|row| Ok((row.try_get(0)?, row.try_get(1)?)))
+            .collect::<Result<_, sqlx::Error>>()
+            .map_err(|e| db_error(instance_id, e))
+    }
+
+    // Dry run: reports what apply_migrations would do without changing the instance
+    pub async fn plan_migrations(&self, instance_id: &str, migration_set_id: &str) -> DataResult<MigrationPlan> {
+        let set = self.get_migration_set(migration_set_id).await?;
+        let mut conn = self.connect(instance_id).await?;
+        let exists: bool = sqlx::query_scalar("SELECT to_regclass($1) IS NOT NULL")
+            .bind(&self.tracking_table)
+            .fetch_one(&mut conn)
+            .await
+            .map_err(|e| db_error(instance_id, e))?;
+        let applied = if exists {
+            self.applied_versions(&mut conn, instance_id).await?
+        } else {
+            HashMap::new()
+        };
+        let _ = conn.close().await;
+
+        let pending = set
+            .pending(&applied)?
+            .into_iter()
+            .map(|m| PlannedMigration {
+                version: m.version,
+                name: m.name.clone(),
+                transactional: m.transactional,
+            })
+            .collect();
+        let mut applied_versions: Vec<i64> = applied.into_keys().collect();
+        applied_versions.sort_unstable();
+        Ok(MigrationPlan {
+            instance_id: instance_id.to_string(),
+            migration_set_id: migration_set_id.to_string(),
+            applied_versions,
+            pending,
+        })
+    }
+
+    pub async fn apply_migrations(&self, instance_id: &str, migration_set_id: &str) -> DataResult<MigrationReport> {
+        let set = self.get_migration_set(migration_set_id).await?;
+        let mut conn = self.connect(instance_id).await?;
+        let locked: bool = sqlx::query_scalar("SELECT pg_try_advisory_lock($1)")
+            .bind(self.lock_key())
+            .fetch_one(&mut conn)
+            .await
+            .map_err(|e| db_error(instance_id, e))?;
+        if !locked {
+            let _ = conn.close().await;
+            return Err(DataError::Conflict(format!(
+                "Migrations are already running on instance {}",
+                instance_id
+            )));
+        }
+
+        let result = self.apply_locked(&mut conn, instance_id, &set).await;
+        let _ = sqlx::query("SELECT pg_advisory_unlock($1)")
+            .bind(self.lock_key())
+            .execute(&mut conn)
+            .await;
+        let _ = conn.close().await;
+        result
+    }
+
+    async fn apply_locked(&self, conn: &mut PgConnection, instance_id: &str, set: &MigrationSet) -> DataResult<MigrationReport> {
+        sqlx::raw_sql(&format!(
+            "CREATE TABLE IF NOT EXISTS {} (
+                version BIGINT PRIMARY KEY,
+                name TEXT NOT NULL,
+                checksum TEXT NOT NULL,
+                migration_set_id TEXT NOT NULL,
+                applied_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+                duration_ms BIGINT NOT NULL
+            )",
+            self.quoted_table()
+        ))
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| db_error(instance_id, e))?;
+
+        let applied = self.applied_versions(conn, instance_id).await?;
+        let pending = set.pending(&applied)?;
+        let mut report = MigrationReport {
+            instance_id: instance_id.to_string(),
+            migration_set_id: set.id.clone(),
+            already_applied: applied.len(),
+            applied: Vec::new(),
+        };
+        let record = format!(
+            "INSERT INTO {} (version, name, checksum, migration_set_id, duration_ms) VALUES ($1, $2, $3, $4, $5)",
+            self.quoted_table()
+        );
+
+        for migration in pending {
+            let started = Instant::now();
+            let failed = |e: sqlx::Error| {
+                DataError::Database(format!(
+                    "Migration {} on {} failed after applying {} of this run: {}",
+                    migration.name,
+                    instance_id,
+                    report.applied.len(),
+                    e
+                ))
+            };
+            let rows_affected = if migration.transactional {
+                let mut tx = conn.begin().await.map_err(failed)?;
+                let result = sqlx::raw_sql(&migration.sql).execute(&mut *tx).await.map_err(failed)?;
+                let duration_ms = started.elapsed().as_millis() as u64;
+                sqlx::query(&record)
+                    .bind(migration.version)
+                    .bind(&migration.name)
+                    .bind(&migration.checksum)
+                    .bind(&set.id)
+                    .bind(duration_ms as i64)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(failed)?;
+                tx.commit().await.map_err(failed)?;
+                result.rows_affected()
+            } else {
+                let result = sqlx::raw_sql(&migration.sql).execute(&mut *conn).await.map_err(failed)?;
+                sqlx::query(&record)
+                    .bind(migration.version)
+                    .bind(&migration.name)
+                    .bind(&migration.checksum)
+                    .bind(&set.id)
+                    .bind(started.elapsed().as_millis() as i64)
+                    .execute(&mut *conn)
+                    .await
+                    .map_err(failed)?;
+                result.rows_affected()
+            };
+            let duration_ms = started.elapsed().as_millis() as u64;
+            info!(
+                "Applied migration {} on {} in {}ms ({} rows)",
+                migration.name, instance_id, duration_ms, rows_affected
+            );
+            report.applied.push(AppliedMigration {
+                version: migration.version,
+                name: migration.name.clone(),
+                transactional: migration.transactional,
+                duration_ms,
+                rows_affected,
+            });
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::testing::postgres_from_env;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_migration_set_ordering_and_checksums() {
+        let set = MigrationSet::new(
+            "orders",
+            vec![
+                MigrationFile::new("0002_add_index.sql", "CREATE INDEX CONCURRENTLY idx ON t (id);"),
+                MigrationFile::new("V1__create.sql", "CREATE TABLE t (id INT);"),
+            ],
+        )
+        .unwrap();
+        let versions: Vec<_> = set.migrations.iter().map(|m| (m.version, m.transactional)).collect();
+        assert_eq!(versions, vec![(1, true), (2, false)]);
+
+        let mut corrupted = MigrationFile::new("0003_x.sql", "SELECT 1;");
+        corrupted.sql.push_str(" -- edited");
+        assert!(matches!(MigrationSet::new("bad", vec![corrupted]), Err(DataError::Validation(_))));
+        assert!(MigrationSet::new(
+            "dup",
+            vec![MigrationFile::new("1_a.sql", "SELECT 1;"), MigrationFile::new("01_b.sql", "SELECT 2;")]
+        )
+        .is_err());
+
+        let applied = HashMap::from([(1, "0".repeat(64))]);
+        assert!(matches!(set.pending(&applied), Err(DataError::Conflict(_))));
+        let applied = HashMap::from([(1, set.migrations[0].checksum.clone())]);
+        assert_eq!(set.pending(&applied).unwrap().len(), 1);
+    }
+
+    fn table_name() -> String {
+        format!("migrations_{}", &uuid::Uuid::new_v4().simple().to_string()[..8])
+    }
+
+    #[tokio::test]
+    async fn test_postgres_apply_and_checksum_drift() {
+        let Some((instance, credentials)) = postgres_from_env() else {
+            eprintln!("SIRSI_TEST_POSTGRES_URL not set, skipping");
+            return;
+        };
+        let data = table_name();
+        let runner = MigrationRunner::new().with_tracking_table(table_name()).unwrap();
+        runner.register_instance(instance.clone(), credentials).await.unwrap();
+        let first = MigrationFile::new("0001_create.sql", format!("CREATE TABLE {} (id INT PRIMARY KEY);", data));
+        let second = MigrationFile::new(
+            "0002_seed.sql",
+            format!("INSERT INTO {} SELECT generate_series(1, 5);", data),
+        );
+        runner
+            .upload_migration_set("v1", vec![first.clone(), second.clone()])
+            .await
+            .unwrap();
+
+        let plan = runner.plan_migrations(&instance.id, "v1").await.unwrap();
+        assert_eq!(plan.pending.len(), 2);
+        assert!(plan.applied_versions.is_empty());
+
+        let report = runner.apply_migrations(&instance.id, "v1").await.unwrap();
+        assert_eq!(report.applied.len(), 2);
+        assert_eq!(report.applied[1].rows_affected, 5);
+        let report = runner.apply_migrations(&instance.id, "v1").await.unwrap();
+        assert_eq!((report.already_applied, report.applied.len()), (2, 0));
+
+        // Editing an applied file blocks both the plan and the apply
+        let edited = MigrationFile::new(
+            "0002_seed.sql",
+            format!("INSERT INTO {} SELECT generate_series(1, 6);", data),
+        );
+        let third = MigrationFile::new("0003_more.sql", format!("INSERT INTO {} VALUES (100);", data));
+        runner
+            .upload_migration_set("v2", vec![first, edited, third])
+            .await
+            .unwrap();
+        assert!(matches!(runner.plan_migrations(&instance.id, "v2").await, Err(DataError::Conflict(_))));
+        assert!(matches!(runner.apply_migrations(&instance.id, "v2").await, Err(DataError::Conflict(_))));
+
+        let mut conn = runner.connect(&instance.id).await.unwrap();
+        let count: i64 = sqlx::query_scalar(&format!("SELECT count(*) FROM {}", data))
+            .fetch_one(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!(count, 5);
+        sqlx::raw_sql(&format!("DROP TABLE {}; DROP TABLE {}", data, runner.quoted_table()))
+            .execute(&mut conn)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_postgres_concurrent_apply_is_excluded() {
+        let Some((instance, credentials)) = postgres_from_env() else {
+            eprintln!("SIRSI_TEST_POSTGRES_URL not set, skipping");
+            return;
+        };
+        let data = table_name();
+        let runner = Arc::new(MigrationRunner::new().with_tracking_table(table_name()).unwrap());
+        runner.register_instance(instance.clone(), credentials).await.unwrap();
+        runner
+            .upload_migration_set(
+                "slow",
+                vec![MigrationFile::new(
+                    "0001_slow.sql",
+                    format!("CREATE TABLE {} (id INT); SELECT pg_sleep(1);", data),
+                )],
+            )
+            .await
+            .unwrap();
+
+        let (a, b) = tokio::join!(
+            runner.apply_migrations(&instance.id, "slow"),
+            async {
+                tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+                runner.apply_migrations(&instance.id, "slow").await
+            }
+        );
+        assert_eq!(a.unwrap().applied.len(), 1);
+        assert!(matches!(b, Err(DataError::Conflict(_))));
+
+        let mut conn = runner.connect(&instance.id).await.unwrap();
+        sqlx::raw_sql(&format!("DROP TABLE {}; DROP TABLE {}", data, runner.quoted_table()))
+            .execute(&mut conn)
+            .await
+            .unwrap();
+    }
+}
@@ -5,6 +5,34 @@ use chrono::{DateTime, Utc};
 
 use crate::error::DataResult;
 
+pub mod cloning;
+pub mod credentials;
+pub mod maintenance;
+pub mod migrations;
+pub mod opensearch;
+pub mod scheduler;
+pub mod verification;
+#[cfg(test)]
+mod testing;
+
+pub use cloning::{
+    CloneOptions, CloneSource, CloneStatus, DatabaseClone, InstanceCloner, MaskingScript, PostgresMaskingExecutor,
+};
+pub use credentials::{
+    AppCredentialConfig, CredentialRotation, CredentialRotator, DatabaseCredentials, PostgresRoleAdmin, RoleAdmin,
+};
+pub use maintenance::SilencingMaintenanceManager;
+pub use migrations::{MigrationFile, MigrationPlan, MigrationReport, MigrationRunner, MigrationSet};
+pub use opensearch::{HttpSearchApi, ManifestApplier, OpenSearchConfig, OpenSearchManager, SearchApi};
+pub use scheduler::{
+    Clock, DeferredOperation, Disruption, MaintenanceActor, MaintenanceScheduler, ManagedOperations, Submission,
+    SubmitOptions, SystemClock, WindowSummary,
+};
+pub use verification::{
+    BackupVerificationResult, BackupVerifier, ManagedCacheSandbox, ManagedDatabaseSandbox, PostgresProbe, RedisProbe,
+    VerificationOutcome, VerificationPolicy,
+};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DatabaseInstance {
     pub id: String,
@@ -25,7 +53,7 @@ pub struct DatabaseInstance {
     pub tags: HashMap<String, String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum DatabaseEngine {
     PostgreSQL,
     MySQL,
@@ -87,6 +115,44 @@ pub struct DatabaseMetrics {
     pub latency_ms: f64,
     pub connections: i32,
     pub replication_lag: Option<i32>,
+    // Engines with disk-based allocation limits (search clusters) report where usage sits
+    #[serde(default)]
+    pub disk_watermark: Option<DiskWatermark>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DiskWatermark {
+    Ok,
+    Low,
+    High,
+    FloodStage,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Green,
+    Yellow,
+    Red,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexHealth {
+    pub name: String,
+    pub status: HealthStatus,
+    pub primary_shards: i32,
+    pub replicas: i32,
+    pub active_shards: i32,
+    pub unassigned_shards: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EngineDetails {
+    pub instance_id: String,
+    pub engine: DatabaseEngine,
+    pub health: HealthStatus,
+    pub node_count: i32,
+    pub indices: Vec<IndexHealth>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -99,6 +165,16 @@ pub struct BackupJob {
     pub completed_at: Option<DateTime<Utc>>,
     pub size_bytes: u64,
     pub storage_location: String,
+    #[serde(default)]
+    pub manifest: Option<BackupManifest>,
+}
+
+// What a backup captured, recorded when it was taken; restore verification compares against it
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub row_counts: HashMap<String, u64>,
+    pub key_count: Option<u64>,
+    pub sample_keys: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -128,6 +204,14 @@ pub trait DatabaseManager: Send + Sync {
     async fn create_backup(&self, instance_id: &str) -> DataResult<BackupJob>;
     async fn restore_backup(&self, backup_id: &str, target_instance_id: &str) -> DataResult<DatabaseInstance>;
     async fn get_metrics(&self, instance_id: &str, window: chrono::Duration) -> DataResult<Vec<DatabaseMetrics>>;
+    // Replaces the app user's password and publishes it to key-vault; the previous password keeps
+    // working until the configured overlap window ends
+    async fn rotate_credentials(&self, instance_id: &str) -> DataResult<CredentialRotation>;
+    // Ephemeral copy from the latest backup or a storage snapshot, optionally masked, deleted
+    // by the clone reaper once its TTL passes
+    async fn clone_instance(&self, source_id: &str, options: CloneOptions) -> DataResult<DatabaseClone>;
+    // Engine-level health that the generic metrics cannot express, e.g. search index status
+    async fn get_engine_details(&self, instance_id: &str) -> DataResult<EngineDetails>;
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -169,6 +253,21 @@ pub struct MaintenanceTask {
     pub started_at: Option<DateTime<Utc>>,
     pub completed_at: Option<DateTime<Utc>>,
     pub description: String,
+    // Silence the instance's alerts for its maintenance window; needs SilencingMaintenanceManager
+    #[serde(default)]
+    pub silence_alerts: bool,
+    #[serde(default)]
+    pub silence_id: Option<String>,
+    // What MaintenanceScheduler runs when the window opens; tasks listed in `depends_on` must
+    // complete first
+    #[serde(default)]
+    pub operation: Option<DeferredOperation>,
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    #[serde(default)]
+    pub timeout_seconds: Option<u64>,
+    #[serde(default)]
+    pub error: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
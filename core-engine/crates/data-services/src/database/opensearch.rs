@@ -0,0 +1,1308 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::sync::RwLock;
+use tracing::{debug, info, warn};
+
+use crate::error::{DataError, DataResult};
+use super::cloning::{CloneOptions, DatabaseClone};
+use super::credentials::CredentialRotation;
+use super::{
+    BackupJob, BackupStatus, BackupType, DatabaseEngine, DatabaseInstance, DatabaseManager, DatabaseMetrics,
+    DatabaseScaling, DiskWatermark, EngineDetails, HealthStatus, IndexHealth, InstanceStatus, ReplicaConfig,
+    ScalingPolicy,
+};
+
+pub const NODE_COUNT_TAG: &str = "sirsi:node-count";
+// `single-node` clusters bootstrap without discovery and cannot grow; `multi-node` ones can
+pub const TOPOLOGY_TAG: &str = "sirsi:search-topology";
+pub const OPENSEARCH_HTTP_PORT: u16 = 9200;
+pub const OPENSEARCH_TRANSPORT_PORT: u16 = 9300;
+pub const RESTARTED_AT_ANNOTATION: &str = "sirsi.io/restarted-at";
+
+const SINGLE_NODE: &str = "single-node";
+const MULTI_NODE: &str = "multi-node";
+const ALLOCATION_EXCLUDE_SETTING: &str = "cluster.routing.allocation.exclude._name";
+// User indices only; system indices are managed by the cluster itself
+const USER_INDICES: &str = "*,-.*";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchMethod {
+    Get,
+    Put,
+    Post,
+    Delete,
+}
+
+// JSON REST calls against the cluster behind `instance`
+#[async_trait]
+pub trait SearchApi: Send + Sync {
+    async fn request(
+        &self,
+        instance: &DatabaseInstance,
+        method: SearchMethod,
+        path: &str,
+        body: Option<Value>,
+    ) -> DataResult<Value>;
+}
+
+// Applies Kubernetes manifests; implemented by whatever holds cluster credentials
+#[async_trait]
+pub trait ManifestApplier: Send + Sync {
+    async fn apply(&self, manifest: &Value) -> DataResult<()>;
+    async fn delete(&self, kind: &str, namespace: &str, name: &str) -> DataResult<()>;
+}
+
+pub struct HttpSearchApi {
+    client: reqwest::Client,
+    scheme: String,
+    basic_auth: Option<(String, String)>,
+}
+
+impl Default for HttpSearchApi {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HttpSearchApi {
+    pub fn new() -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            scheme: "http".to_string(),
+            basic_auth: None,
+        }
+    }
+
+    pub fn with_tls(mut self) -> Self {
+        self.scheme = "https".to_string();
+        self
+    }
+
+    pub fn with_basic_auth(mut self, username: impl Into<String>, password: impl Into<String>) -> Self {
+        self.basic_auth = Some((username.into(), password.into()));
+        self
+    }
+}
+
+#[async_trait]
+impl SearchApi for HttpSearchApi {
+    async fn request(
+        &self,
+        instance: &DatabaseInstance,
+        method: SearchMethod,
+        path: &str,
+        body: Option<Value>,
+    ) -> DataResult<Value> {
+        let url = format!(
+            "{}://{}:{}/{}",
+            self.scheme,
+            instance.endpoint,
+            instance.port,
+            path.trim_start_matches('/')
+        );
+        let mut request = match method {
+            SearchMethod::Get => self.client.get(&url),
+            SearchMethod::Put => self.client.put(&url),
+            SearchMethod::Post => self.client.post(&url),
+            SearchMethod::Delete => self.client.delete(&url),
+        };
+        if let Some((username, password)) = &self.basic_auth {
+            request = request.basic_auth(username, Some(password));
+        }
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| DataError::Unavailable(format!("{} {} on {} failed: {}", method_name(method), path, instance.id, e)))?;
+        let status = response.status();
+        let text = response
+            .text()
+            .await
+            .map_err(|e| DataError::Provider(format!("Reading response from {} failed: {}", instance.id, e)))?;
+        if !status.is_success() {
+            return Err(DataError::from_http_status(
+                status.as_u16(),
+                format!("{} {} on {}: {}", method_name(method), path, instance.id, text),
+            ));
+        }
+        if text.is_empty() {
+            return Ok(Value::Null);
+        }
+        serde_json::from_str(&text)
+            .map_err(|e| DataError::Provider(format!("Invalid JSON from {} for {}: {}", instance.id, path, e)))
+    }
+}
+
+fn method_name(method: SearchMethod) -> &'static str {
+    match method {
+        SearchMethod::Get => "GET",
+        SearchMethod::Put => "PUT",
+        SearchMethod::Post => "POST",
+        SearchMethod::Delete => "DELETE",
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpenSearchConfig {
+    pub namespace: String,
+    pub image: String,
+    pub storage_class: Option<String>,
+    pub heap_mb: i32,
+    pub snapshot_repository: String,
+    // `s3` (needs repository-s3) or `fs` (needs path.repo); settings are passed through, with the
+    // instance id appended to the s3 base_path or fs location so clusters never share a path
+    pub snapshot_repository_type: String,
+    pub snapshot_settings: HashMap<String, String>,
+    pub drain_timeout_seconds: u64,
+}
+
+impl Default for OpenSearchConfig {
+    fn default() -> Self {
+        Self {
+            namespace: "sirsi-data".to_string(),
+            image: "opensearchproject/opensearch:2.11.1".to_string(),
+            storage_class: None,
+            heap_mb: 1024,
+            snapshot_repository: "sirsi-snapshots".to_string(),
+            snapshot_repository_type: "s3".to_string(),
+            snapshot_settings: HashMap::from([("bucket".to_string(), "sirsi-search-snapshots".to_string())]),
+            drain_timeout_seconds: 600,
+        }
+    }
+}
+
+pub fn node_count(instance: &DatabaseInstance) -> DataResult<i32> {
+    match instance.tags.get(NODE_COUNT_TAG) {
+        None => Ok(1),
+        Some(count) => count
+            .parse::<i32>()
+            .ok()
+            .filter(|c| *c >= 1)
+            .ok_or_else(|| DataError::Validation(format!("Invalid {} tag {} on {}", NODE_COUNT_TAG, count, instance.id))),
+    }
+}
+
+fn is_single_node(instance: &DatabaseInstance) -> bool {
+    instance.tags.get(TOPOLOGY_TAG).map(String::as_str) == Some(SINGLE_NODE)
+}
+
+// Kubernetes object names must be DNS labels
+fn cluster_name(instance: &DatabaseInstance) -> String {
+    dns_label(&instance.id)
+}
+
+fn dns_label(id: &str) -> String {
+    id.to_ascii_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect::<String>()
+        .trim_matches('-')
+        .to_string()
+}
+
+fn node_name(instance: &DatabaseInstance, ordinal: i32) -> String {
+    format!("{}-{}", cluster_name(instance), ordinal)
+}
+
+pub fn service_manifest(instance: &DatabaseInstance, config: &OpenSearchConfig) -> Value {
+    let name = cluster_name(instance);
+    json!({
+        "apiVersion": "v1",
+        "kind": "Service",
+        "metadata": {
+            "name": name,
+            "namespace": config.namespace,
+            "labels": { "app.kubernetes.io/name": "opensearch", "app.kubernetes.io/instance": name },
+        },
+        "spec": {
+            "clusterIP": "None",
+            "publishNotReadyAddresses": true,
+            "selector": { "app.kubernetes.io/instance": name },
+            "ports": [
+                { "name": "http", "port": OPENSEARCH_HTTP_PORT },
+                { "name": "transport", "port": OPENSEARCH_TRANSPORT_PORT },
+            ],
+        },
+    })
+}
+
+pub fn statefulset_manifest(
+    instance: &DatabaseInstance,
+    config: &OpenSearchConfig,
+    replicas: i32,
+    restarted_at: Option<DateTime<Utc>>,
+) -> Value {
+    let name = cluster_name(instance);
+    let mut env = vec![
+        json!({ "name": "cluster.name", "value": name }),
+        json!({ "name": "node.name", "valueFrom": { "fieldRef": { "fieldPath": "metadata.name" } } }),
+        json!({ "name": "OPENSEARCH_JAVA_OPTS", "value": format!("-Xms{0}m -Xmx{0}m", config.heap_mb) }),
+    ];
+    if let Some(location) = config.snapshot_settings.get("location") {
+        env.push(json!({ "name": "path.repo", "value": location }));
+    }
+    if is_single_node(instance) {
+        env.push(json!({ "name": "discovery.type", "value": "single-node" }));
+    } else {
+        // Bootstrap voting only matters on first start, so only the first three ordinals vote
+        let initial: Vec<String> = (0..node_count(instance).unwrap_or(1).min(3)).map(|i| node_name(instance, i)).collect();
+        env.push(json!({ "name": "discovery.seed_hosts", "value": name }));
+        env.push(json!({ "name": "cluster.initial_cluster_manager_nodes", "value": initial.join(",") }));
+    }
+    let mut annotations = serde_json::Map::new();
+    if let Some(at) = restarted_at {
+        annotations.insert(RESTARTED_AT_ANNOTATION.to_string(), json!(at.to_rfc3339()));
+    }
+    let mut volume_spec = json!({
+        "accessModes": ["ReadWriteOnce"],
+        "resources": { "requests": { "storage": format!("{}Gi", instance.storage_gb) } },
+    });
+    if let Some(class) = &config.storage_class {
+        volume_spec["storageClassName"] = json!(class);
+    }
+
+    json!({
+        "apiVersion": "apps/v1",
+        "kind": "StatefulSet",
+        "metadata": {
+            "name": name,
+            "namespace": config.namespace,
+            "labels": { "app.kubernetes.io/name": "opensearch", "app.kubernetes.io/instance": name },
+        },
+        "spec": {
+            "serviceName": name,
+            "replicas": replicas,
+            // Nodes join and leave one at a time, highest ordinal first
+            "podManagementPolicy": "OrderedReady",
+            "selector": { "matchLabels": { "app.kubernetes.io/instance": name } },
+            "template": {
+                "metadata": {
+                    "labels": { "app.kubernetes.io/name": "opensearch", "app.kubernetes.io/instance": name },
+                    "annotations": annotations,
+                },
+                "spec": {
+                    "containers": [{
+                        "name": "opensearch",
+                        "image": config.image,
+                        "env": env,
+                        "ports": [
+                            { "name": "http", "containerPort": OPENSEARCH_HTTP_PORT },
+                            { "name": "transport", "containerPort": OPENSEARCH_TRANSPORT_PORT },
+                        ],
+                        "readinessProbe": { "tcpSocket": { "port": OPENSEARCH_HTTP_PORT } },
+                        "volumeMounts": [{ "name": "data", "mountPath": "/usr/share/opensearch/data" }],
+                    }],
+                },
+            },
+            "volumeClaimTemplates": [{ "metadata": { "name": "data" }, "spec": volume_spec }],
+        },
+    })
+}
+
+// Percent of disk used at which each watermark trips
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DiskWatermarks {
+    pub low: f64,
+    pub high: f64,
+    pub flood_stage: f64,
+}
+
+impl Default for DiskWatermarks {
+    fn default() -> Self {
+        Self {
+            low: 85.0,
+            high: 90.0,
+            flood_stage: 95.0,
+        }
+    }
+}
+
+// Accepts "85%" and ratios like "0.85"; absolute free-space values fall back to the default
+fn watermark_percent(value: &Value) -> Option<f64> {
+    let text = value.as_str()?.trim();
+    match text.strip_suffix('%') {
+        Some(percent) => percent.trim().parse().ok(),
+        None => text.parse::<f64>().ok().filter(|r| *r <= 1.0).map(|r| r * 100.0),
+    }
+}
+
+impl DiskWatermarks {
+    // From `GET _cluster/settings?include_defaults=true&flat_settings=true`; transient settings
+    // override persistent ones, which override defaults
+    pub fn from_settings(settings: &Value) -> Self {
+        let defaults = Self::default();
+        let lookup = |key: &str, fallback: f64| {
+            ["transient", "persistent", "defaults"]
+                .iter()
+                .find_map(|scope| settings.get(scope).and_then(|s| s.get(key)).and_then(watermark_percent))
+                .unwrap_or(fallback)
+        };
+        Self {
+            low: lookup("cluster.routing.allocation.disk.watermark.low", defaults.low),
+            high: lookup("cluster.routing.allocation.disk.watermark.high", defaults.high),
+            flood_stage: lookup("cluster.routing.allocation.disk.watermark.flood_stage", defaults.flood_stage),
+        }
+    }
+
+    pub fn status(&self, used_percent: f64) -> DiskWatermark {
+        if used_percent >= self.flood_stage {
+            DiskWatermark::FloodStage
+        } else if used_percent >= self.high {
+            DiskWatermark::High
+        } else if used_percent >= self.low {
+            DiskWatermark::Low
+        } else {
+            DiskWatermark::Ok
+        }
+    }
+}
+
+fn number(value: &Value, pointer: &str) -> f64 {
+    value.pointer(pointer).and_then(Value::as_f64).unwrap_or(0.0)
+}
+
+fn percent(part: f64, whole: f64) -> f64 {
+    if whole > 0.0 {
+        part / whole * 100.0
+    } else {
+        0.0
+    }
+}
+
+// Watermarks apply per node, so the status reflects the fullest node rather than the cluster
+// average that `disk_utilization` reports
+pub fn metrics_from_stats(
+    instance_id: &str,
+    cluster_stats: &Value,
+    node_stats: &Value,
+    watermarks: &DiskWatermarks,
+    timestamp: DateTime<Utc>,
+) -> DatabaseMetrics {
+    let heap_used = number(cluster_stats, "/nodes/jvm/mem/heap_used_in_bytes");
+    let heap_max = number(cluster_stats, "/nodes/jvm/mem/heap_max_in_bytes");
+    let disk_total = number(cluster_stats, "/nodes/fs/total_in_bytes");
+    let disk_available = number(cluster_stats, "/nodes/fs/available_in_bytes");
+
+    let nodes: Vec<&Value> = node_stats
+        .get("nodes")
+        .and_then(Value::as_object)
+        .map(|nodes| nodes.values().collect())
+        .unwrap_or_default();
+    let fullest = nodes
+        .iter()
+        .map(|node| {
+            let total = number(node, "/fs/total/total_in_bytes");
+            percent(total - number(node, "/fs/total/available_in_bytes"), total)
+        })
+        .fold(None, |worst: Option<f64>, used| Some(worst.map_or(used, |w| w.max(used))));
+    let connections: f64 = nodes.iter().map(|node| number(node, "/http/current_open")).sum();
+    let query_total: f64 = nodes.iter().map(|node| number(node, "/indices/search/query_total")).sum();
+    let query_millis: f64 = nodes.iter().map(|node| number(node, "/indices/search/query_time_in_millis")).sum();
+
+    DatabaseMetrics {
+        instance_id: instance_id.to_string(),
+        timestamp,
+        cpu_utilization: number(cluster_stats, "/nodes/process/cpu/percent"),
+        memory_utilization: percent(heap_used, heap_max),
+        disk_utilization: percent(disk_total - disk_available, disk_total),
+        iops: 0,
+        latency_ms: if query_total > 0.0 { query_millis / query_total } else { 0.0 },
+        connections: connections as i32,
+        replication_lag: None,
+        disk_watermark: fullest.map(|used| watermarks.status(used)),
+    }
+}
+
+fn health_status(value: Option<&Value>) -> HealthStatus {
+    match value.and_then(Value::as_str) {
+        Some("green") => HealthStatus::Green,
+        Some("yellow") => HealthStatus::Yellow,
+        _ => HealthStatus::Red,
+    }
+}
+
+// From `GET _cluster/health?level=indices`
+pub fn index_health(health: &Value) -> Vec<IndexHealth> {
+    let mut indices: Vec<IndexHealth> = health
+        .get("indices")
+        .and_then(Value::as_object)
+        .map(|indices| {
+            indices
+                .iter()
+                .map(|(name, index)| IndexHealth {
+                    name: name.clone(),
+                    status: health_status(index.get("status")),
+                    primary_shards: number(index, "/number_of_shards") as i32,
+                    replicas: number(index, "/number_of_replicas") as i32,
+                    active_shards: number(index, "/active_shards") as i32,
+                    unassigned_shards: number(index, "/unassigned_shards") as i32,
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    indices.sort_by(|a, b| a.name.cmp(&b.name));
+    indices
+}
+
+// A replica is never allocated next to its primary, so every index needs 1 + replicas nodes to
+// stay green; shrinking below that would leave copies unassigned
+pub fn check_scale_in(target_nodes: i32, indices: &[IndexHealth]) -> DataResult<()> {
+    let blocking: Vec<String> = indices
+        .iter()
+        .filter(|index| index.replicas + 1 > target_nodes)
+        .map(|index| format!("{} ({} replicas)", index.name, index.replicas))
+        .collect();
+    if blocking.is_empty() {
+        Ok(())
+    } else {
+        Err(DataError::Conflict(format!(
+            "Scaling in to {} nodes would leave replicas unassigned for {}",
+            target_nodes,
+            blocking.join(", ")
+        )))
+    }
+}
+
+pub struct OpenSearchManager {
+    api: Arc<dyn SearchApi>,
+    manifests: Arc<dyn ManifestApplier>,
+    config: OpenSearchConfig,
+    instances: RwLock<HashMap<String, DatabaseInstance>>,
+    backups: RwLock<HashMap<String, BackupJob>>,
+    policies: RwLock<HashMap<String, ScalingPolicy>>,
+    replicas: RwLock<HashMap<String, ReplicaConfig>>,
+    last_scaled: RwLock<HashMap<String, DateTime<Utc>>>,
+    poll_interval: Duration,
+}
+
+impl OpenSearchManager {
+    pub fn new(api: Arc<dyn SearchApi>, manifests: Arc<dyn ManifestApplier>, config: OpenSearchConfig) -> Self {
+        Self {
+            api,
+            manifests,
+            config,
+            instances: RwLock::new(HashMap::new()),
+            backups: RwLock::new(HashMap::new()),
+            policies: RwLock::new(HashMap::new()),
+            replicas: RwLock::new(HashMap::new()),
+            last_scaled: RwLock::new(HashMap::new()),
+            poll_interval: Duration::from_secs(5),
+        }
+    }
+
+    pub fn with_poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    async fn call(&self, instance: &DatabaseInstance, method: SearchMethod, path: &str, body: Option<Value>) -> DataResult<Value> {
+        self.api.request(instance, method, path, body).await
+    }
+
+    async fn stored(&self, id: &str) -> DataResult<DatabaseInstance> {
+        self.instances
+            .read()
+            .await
+            .get(id)
+            .cloned()
+            .ok_or_else(|| DataError::NotFound(format!("Search cluster {} not found", id)))
+    }
+
+    async fn store(&self, instance: &DatabaseInstance) {
+        self.instances.write().await.insert(instance.id.clone(), instance.clone());
+    }
+
+    async fn apply_statefulset(&self, instance: &DatabaseInstance, replicas: i32, restarted_at: Option<DateTime<Utc>>) -> DataResult<()> {
+        self.manifests
+            .apply(&statefulset_manifest(instance, &self.config, replicas, restarted_at))
+            .await
+    }
+
+    async fn health(&self, instance: &DatabaseInstance) -> DataResult<Value> {
+        self.call(instance, SearchMethod::Get, "_cluster/health?level=indices", None).await
+    }
+
+    fn repository_settings(&self, source_id: &str, readonly: bool) -> Value {
+        let mut settings: serde_json::Map<String, Value> = self
+            .config
+            .snapshot_settings
+            .iter()
+            .map(|(k, v)| (k.clone(), json!(v)))
+            .collect();
+        match self.config.snapshot_repository_type.as_str() {
+            "fs" => {
+                let location = self.config.snapshot_settings.get("location").cloned().unwrap_or_default();
+                settings.insert("location".into(), json!(format!("{}/{}", location.trim_end_matches('/'), source_id)));
+            }
+            _ => {
+                settings.insert("base_path".into(), json!(source_id));
+            }
+        }
+        if readonly {
+            settings.insert("readonly".into(), json!(true));
+        }
+        json!({ "type": self.config.snapshot_repository_type, "settings": settings })
+    }
+
+    // Restores read from the source cluster's path through a read-only repository of their own
+    async fn ensure_repository(&self, instance: &DatabaseInstance, source_id: &str) -> DataResult<String> {
+        let (name, readonly) = if source_id == instance.id {
+            (self.config.snapshot_repository.clone(), false)
+        } else {
+            (format!("{}-from-{}", self.config.snapshot_repository, dns_label(source_id)), true)
+        };
+        self.call(
+            instance,
+            SearchMethod::Put,
+            &format!("_snapshot/{}", name),
+            Some(self.repository_settings(source_id, readonly)),
+        )
+        .await?;
+        Ok(name)
+    }
+
+    async fn refresh_backup(&self, backup_id: &str) -> DataResult<BackupJob> {
+        let mut job = self
+            .backups
+            .read()
+            .await
+            .get(backup_id)
+            .cloned()
+            .ok_or_else(|| DataError::NotFound(format!("Backup {} not found", backup_id)))?;
+        if !matches!(job.status, BackupStatus::InProgress) {
+            return Ok(job);
+        }
+        let instance = self.stored(&job.instance_id).await?;
+        let snapshot = self
+            .call(
+                &instance,
+                SearchMethod::Get,
+                &format!("_snapshot/{}/{}", self.config.snapshot_repository, backup_id),
+                None,
+            )
+            .await?;
+        let entry = snapshot.pointer("/snapshots/0").cloned().unwrap_or(Value::Null);
+        match entry.get("state").and_then(Value::as_str) {
+            Some("SUCCESS") => {
+                job.status = BackupStatus::Completed;
+                job.completed_at = entry
+                    .get("end_time_in_millis")
+                    .and_then(Value::as_i64)
+                    .and_then(DateTime::<Utc>::from_timestamp_millis)
+                    .or(Some(Utc::now()));
+            }
+            Some("FAILED") | Some("PARTIAL") => job.status = BackupStatus::Failed,
+            _ => {}
+        }
+        self.backups.write().await.insert(job.id.clone(), job.clone());
+        Ok(job)
+    }
+
+    // Scale-out only grows the StatefulSet. Scale-in is checked against replica counts, then the
+    // leaving nodes (highest ordinals) are excluded from allocation and drained before they stop.
+    pub async fn scale_nodes(&self, instance_id: &str, target: i32) -> DataResult<DatabaseInstance> {
+        let mut instance = self.stored(instance_id).await?;
+        let current = node_count(&instance)?;
+        if target < 1 {
+            return Err(DataError::Validation(format!("{} needs at least one node", instance_id)));
+        }
+        if target == current {
+            return Ok(instance);
+        }
+        if is_single_node(&instance) {
+            return Err(DataError::Validation(format!(
+                "{} was created as a single-node cluster; recreate it as multi-node to scale",
+                instance_id
+            )));
+        }
+
+        if target < current {
+            let health = self.health(&instance).await?;
+            if health_status(health.get("status")) != HealthStatus::Green {
+                return Err(DataError::Conflict(format!("{} must be green before scaling in", instance_id)));
+            }
+            check_scale_in(target, &index_health(&health))?;
+            let leaving: Vec<String> = (target..current).map(|i| node_name(&instance, i)).collect();
+            self.set_allocation_exclusion(&instance, Some(leaving.join(","))).await?;
+            let drained = self.drain(&instance, &leaving).await;
+            if let Err(e) = drained {
+                self.set_allocation_exclusion(&instance, None).await?;
+                return Err(e);
+            }
+            self.call(
+                &instance,
+                SearchMethod::Post,
+                &format!("_cluster/voting_config_exclusions?node_names={}", leaving.join(",")),
+                None,
+            )
+            .await?;
+            self.apply_statefulset_count(&mut instance, target).await?;
+            self.call(
+                &instance,
+                SearchMethod::Delete,
+                "_cluster/voting_config_exclusions?wait_for_removal=false",
+                None,
+            )
+            .await?;
+            self.set_allocation_exclusion(&instance, None).await?;
+        } else {
+            self.apply_statefulset_count(&mut instance, target).await?;
+        }
+        info!("Scaled search cluster {} from {} to {} nodes", instance_id, current, target);
+        Ok(instance)
+    }
+
+    async fn apply_statefulset_count(&self, instance: &mut DatabaseInstance, target: i32) -> DataResult<()> {
+        instance.tags.insert(NODE_COUNT_TAG.to_string(), target.to_string());
+        self.apply_statefulset(instance, target, None).await?;
+        instance.updated_at = Utc::now();
+        self.store(instance).await;
+        Ok(())
+    }
+
+    async fn set_allocation_exclusion(&self, instance: &DatabaseInstance, nodes: Option<String>) -> DataResult<()> {
+        self.call(
+            instance,
+            SearchMethod::Put,
+            "_cluster/settings",
+            Some(json!({ "persistent": { ALLOCATION_EXCLUDE_SETTING: nodes } })),
+        )
+        .await
+        .map(|_| ())
+    }
+
+    async fn drain(&self, instance: &DatabaseInstance, leaving: &[String]) -> DataResult<()> {
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(self.config.drain_timeout_seconds);
+        loop {
+            let shards = self
+                .call(instance, SearchMethod::Get, "_cat/shards?format=json&h=index,shard,node", None)
+                .await?;
+            let remaining = shards
+                .as_array()
+                .map(|shards| {
+                    shards
+                        .iter()
+                        .filter(|s| s.get("node").and_then(Value::as_str).is_some_and(|n| leaving.iter().any(|l| l == n)))
+                        .count()
+                })
+                .unwrap_or(0);
+            if remaining == 0 {
+                return Ok(());
+            }
+            if tokio::time::Instant::now() >= deadline {
+                return Err(DataError::Unavailable(format!(
+                    "{} shards still on leaving nodes of {} after {}s",
+                    remaining, instance.id, self.config.drain_timeout_seconds
+                )));
+            }
+            debug!("Waiting for {} shards to leave {:?} on {}", remaining, leaving, instance.id);
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+
+    // One policy step: nodes sized so the busier of CPU and heap lands on its target, within
+    // the policy's node bounds and cooldown
+    pub async fn evaluate_scaling(&self, instance_id: &str) -> DataResult<Option<i32>> {
+        let policy = self.get_scaling_policy(instance_id).await?;
+        let (min, max) = policy_bounds(&policy)?;
+        if let Some(at) = self.last_scaled.read().await.get(instance_id) {
+            if Utc::now() - *at < chrono::Duration::seconds(policy.cooldown_seconds as i64) {
+                return Ok(None);
+            }
+        }
+        let instance = self.stored(instance_id).await?;
+        let current = node_count(&instance)?;
+        let Some(metrics) = self.get_metrics(instance_id, chrono::Duration::minutes(5)).await?.pop() else {
+            return Ok(None);
+        };
+        let pressure = (metrics.cpu_utilization / policy.target_cpu_utilization)
+            .max(metrics.memory_utilization / policy.target_memory_utilization);
+        let desired = ((current as f64 * pressure).ceil() as i32).clamp(min, max);
+        if desired == current {
+            return Ok(None);
+        }
+        match self.scale_nodes(instance_id, desired).await {
+            Ok(_) => {
+                self.last_scaled.write().await.insert(instance_id.to_string(), Utc::now());
+                Ok(Some(desired))
+            }
+            // A refused scale-in leaves the cluster as it is; the next evaluation retries
+            Err(DataError::Conflict(msg)) if desired < current => {
+                warn!("Not scaling in {}: {}", instance_id, msg);
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+fn policy_bounds(policy: &ScalingPolicy) -> DataResult<(i32, i32)> {
+    let parse = |value: &str| value.trim().parse::<i32>().ok().filter(|n| *n >= 1);
+    match (parse(&policy.min_capacity), parse(&policy.max_capacity)) {
+        (Some(min), Some(max)) if min <= max => Ok((min, max)),
+        _ => Err(DataError::Validation(format!(
+            "Search cluster capacity is a node count; got {}..{} for {}",
+            policy.min_capacity, policy.max_capacity, policy.instance_id
+        ))),
+    }
+}
+
+fn ensure_search_engine(instance: &DatabaseInstance) -> DataResult<()> {
+    if matches!(instance.engine, DatabaseEngine::Elasticsearch) {
+        Ok(())
+    } else {
+        Err(DataError::Validation(format!(
+            "{} is a {:?} instance, not a search cluster",
+            instance.id, instance.engine
+        )))
+    }
+}
+
+#[async_trait]
+impl DatabaseManager for OpenSearchManager {
+    async fn create_instance(&self, mut config: DatabaseInstance) -> DataResult<DatabaseInstance> {
+        ensure_search_engine(&config)?;
+        if self.instances.read().await.contains_key(&config.id) {
+            return Err(DataError::Conflict(format!("Search cluster {} already exists", config.id)));
+        }
+        let nodes = node_count(&config)?;
+        config
+            .tags
+            .entry(TOPOLOGY_TAG.to_string())
+            .or_insert_with(|| if nodes == 1 { SINGLE_NODE } else { MULTI_NODE }.to_string());
+        if is_single_node(&config) && nodes > 1 {
+            return Err(DataError::Validation(format!("Single-node cluster {} cannot have {} nodes", config.id, nodes)));
+        }
+        config.endpoint = format!("{}.{}.svc", cluster_name(&config), self.config.namespace);
+        config.port = OPENSEARCH_HTTP_PORT;
+        config.status = InstanceStatus::Creating;
+        config.updated_at = Utc::now();
+
+        self.manifests.apply(&service_manifest(&config, &self.config)).await?;
+        self.apply_statefulset(&config, nodes, None).await?;
+        self.store(&config).await;
+        info!("Provisioning {}-node search cluster {}", nodes, config.id);
+        Ok(config)
+    }
+
+    // Node count changes go through scale_nodes so scale-in keeps its guard
+    async fn modify_instance(&self, mut instance: DatabaseInstance) -> DataResult<DatabaseInstance> {
+        ensure_search_engine(&instance)?;
+        let existing = self.stored(&instance.id).await?;
+        let target = node_count(&instance)?;
+        instance.tags.insert(NODE_COUNT_TAG.to_string(), node_count(&existing)?.to_string());
+        if let Some(topology) = existing.tags.get(TOPOLOGY_TAG) {
+            instance.tags.insert(TOPOLOGY_TAG.to_string(), topology.clone());
+        }
+        instance.endpoint = existing.endpoint;
+        instance.port = existing.port;
+        instance.updated_at = Utc::now();
+        self.apply_statefulset(&instance, node_count(&instance)?, None).await?;
+        self.store(&instance).await;
+        self.scale_nodes(&instance.id, target).await
+    }
+
+    async fn delete_instance(&self, id: &str) -> DataResult<()> {
+        let instance = self.stored(id).await?;
+        let name = cluster_name(&instance);
+        for kind in ["StatefulSet", "Service"] {
+            match self.manifests.delete(kind, &self.config.namespace, &name).await {
+                Ok(()) | Err(DataError::NotFound(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        self.instances.write().await.remove(id);
+        info!("Deleted search cluster {}", id);
+        Ok(())
+    }
+
+    async fn get_instance(&self, id: &str) -> DataResult<DatabaseInstance> {
+        let mut instance = self.stored(id).await?;
+        if matches!(instance.status, InstanceStatus::Creating | InstanceStatus::Modifying | InstanceStatus::Restoring) {
+            match self.call(&instance, SearchMethod::Get, "_cluster/health", None).await {
+                Ok(health) if health_status(health.get("status")) != HealthStatus::Red => {
+                    instance.status = InstanceStatus::Available;
+                    self.store(&instance).await;
+                }
+                Ok(_) => {}
+                Err(e) => debug!("Search cluster {} not reachable yet: {}", id, e),
+            }
+        }
+        Ok(instance)
+    }
+
+    async fn list_instances(&self) -> DataResult<Vec<DatabaseInstance>> {
+        Ok(self.instances.read().await.values().cloned().collect())
+    }
+
+    async fn start_instance(&self, id: &str) -> DataResult<()> {
+        let mut instance = self.stored(id).await?;
+        self.apply_statefulset(&instance, node_count(&instance)?, None).await?;
+        instance.status = InstanceStatus::Modifying;
+        self.store(&instance).await;
+        Ok(())
+    }
+
+    async fn stop_instance(&self, id: &str) -> DataResult<()> {
+        let mut instance = self.stored(id).await?;
+        self.apply_statefulset(&instance, 0, None).await?;
+        instance.status = InstanceStatus::Modifying;
+        self.store(&instance).await;
+        Ok(())
+    }
+
+    // Changing a template annotation makes the StatefulSet controller roll every pod in order
+    async fn restart_instance(&self, id: &str) -> DataResult<()> {
+        let instance = self.stored(id).await?;
+        self.apply_statefulset(&instance, node_count(&instance)?, Some(Utc::now())).await
+    }
+
+    async fn create_backup(&self, instance_id: &str) -> DataResult<BackupJob> {
+        let instance = self.stored(instance_id).await?;
+        let repository = self.ensure_repository(&instance, instance_id).await?;
+        let snapshot = format!("sirsi-{}", uuid::Uuid::new_v4().simple());
+        self.call(
+            &instance,
+            SearchMethod::Put,
+            &format!("_snapshot/{}/{}?wait_for_completion=false", repository, snapshot),
+            Some(json!({ "indices": USER_INDICES, "include_global_state": false })),
+        )
+        .await?;
+        let location = match self.config.snapshot_repository_type.as_str() {
+            "s3" => format!(
+                "s3://{}/{}/{}",
+                self.config.snapshot_settings.get("bucket").cloned().unwrap_or_default(),
+                instance_id,
+                snapshot
+            ),
+            kind => format!("{}:{}/{}", kind, instance_id, snapshot),
+        };
+        let job = BackupJob {
+            id: snapshot,
+            instance_id: instance_id.to_string(),
+            status: BackupStatus::InProgress,
+            type_: BackupType::Snapshot,
+            started_at: Utc::now(),
+            completed_at: None,
+            size_bytes: 0,
+            storage_location: location,
+            manifest: None,
+        };
+        self.backups.write().await.insert(job.id.clone(), job.clone());
+        Ok(job)
+    }
+
+    // Restores user indices; indices that already exist on the target must be closed or deleted
+    // first, which the cluster reports as a conflict
+    async fn restore_backup(&self, backup_id: &str, target_instance_id: &str) -> DataResult<DatabaseInstance> {
+        let job = self.refresh_backup(backup_id).await?;
+        if !matches!(job.status, BackupStatus::Completed) {
+            return Err(DataError::Conflict(format!("Backup {} is {:?}, not completed", backup_id, job.status)));
+        }
+        let mut target = self.stored(target_instance_id).await?;
+        let repository = self.ensure_repository(&target, &job.instance_id).await?;
+        self.call(
+            &target,
+            SearchMethod::Post,
+            &format!("_snapshot/{}/{}/_restore", repository, backup_id),
+            Some(json!({ "indices": USER_INDICES, "include_global_state": false })),
+        )
+        .await?;
+        target.status = InstanceStatus::Restoring;
+        target.updated_at = Utc::now();
+        self.store(&target).await;
+        Ok(target)
+    }
+
+    async fn get_metrics(&self, instance_id: &str, _window: chrono::Duration) -> DataResult<Vec<DatabaseMetrics>> {
+        let instance = self.stored(instance_id).await?;
+        let cluster_stats = self.call(&instance, SearchMethod::Get, "_cluster/stats", None).await?;
+        let node_stats = self
+            .call(&instance, SearchMethod::Get, "_nodes/stats/fs,http,indices", None)
+            .await?;
+        let settings = self
+            .call(
+                &instance,
+                SearchMethod::Get,
+                "_cluster/settings?include_defaults=true&flat_settings=true",
+                None,
+            )
+            .await?;
+        Ok(vec![metrics_from_stats(
+            instance_id,
+            &cluster_stats,
+            &node_stats,
+            &DiskWatermarks::from_settings(&settings),
+            Utc::now(),
+        )])
+    }
+
+    async fn rotate_credentials(&self, instance_id: &str) -> DataResult<CredentialRotation> {
+        Err(DataError::Validation(format!(
+            "Credential rotation is not supported for search cluster {}",
+            instance_id
+        )))
+    }
+
+    async fn clone_instance(&self, source_id: &str, _options: CloneOptions) -> DataResult<DatabaseClone> {
+        Err(DataError::Validation(format!(
+            "Cloning is not supported for search cluster {}; restore a snapshot into a new cluster instead",
+            source_id
+        )))
+    }
+
+    async fn get_engine_details(&self, instance_id: &str) -> DataResult<EngineDetails> {
+        let instance = self.stored(instance_id).await?;
+        let health = self.health(&instance).await?;
+        Ok(EngineDetails {
+            instance_id: instance_id.to_string(),
+            engine: DatabaseEngine::Elasticsearch,
+            health: health_status(health.get("status")),
+            node_count: number(&health, "/number_of_nodes") as i32,
+            indices: index_health(&health),
+        })
+    }
+}
+
+#[async_trait]
+impl DatabaseScaling for OpenSearchManager {
+    async fn configure_scaling(&self, policy: ScalingPolicy) -> DataResult<()> {
+        self.stored(&policy.instance_id).await?;
+        policy_bounds(&policy)?;
+        if policy.target_cpu_utilization <= 0.0 || policy.target_memory_utilization <= 0.0 {
+            return Err(DataError::Validation("Scaling targets must be positive".into()));
+        }
+        self.policies.write().await.insert(policy.instance_id.clone(), policy);
+        Ok(())
+    }
+
+    async fn get_scaling_policy(&self, instance_id: &str) -> DataResult<ScalingPolicy> {
+        self.policies
+            .read()
+            .await
+            .get(instance_id)
+            .cloned()
+            .ok_or_else(|| DataError::NotFound(format!("No scaling policy for {}", instance_id)))
+    }
+
+    async fn update_scaling_policy(&self, policy: ScalingPolicy) -> DataResult<()> {
+        self.get_scaling_policy(&policy.instance_id).await?;
+        self.configure_scaling(policy).await
+    }
+
+    // The replica count applies to every user index; it must leave room for each copy on its
+    // own node
+    async fn configure_replicas(&self, config: ReplicaConfig) -> DataResult<()> {
+        let instance = self.stored(&config.instance_id).await?;
+        let nodes = node_count(&instance)?;
+        if config.replica_count < 0 || config.replica_count >= nodes {
+            return Err(DataError::Validation(format!(
+                "{} replicas need at least {} nodes; {} has {}",
+                config.replica_count,
+                config.replica_count + 1,
+                config.instance_id,
+                nodes
+            )));
+        }
+        self.call(
+            &instance,
+            SearchMethod::Put,
+            &format!("{}/_settings", USER_INDICES),
+            Some(json!({ "index": { "number_of_replicas": config.replica_count } })),
+        )
+        .await?;
+        self.replicas.write().await.insert(config.instance_id.clone(), config);
+        Ok(())
+    }
+
+    async fn get_replica_config(&self, instance_id: &str) -> DataResult<ReplicaConfig> {
+        self.replicas
+            .read()
+            .await
+            .get(instance_id)
+            .cloned()
+            .ok_or_else(|| DataError::NotFound(format!("No replica config for {}", instance_id)))
+    }
+
+    async fn update_replica_config(&self, config: ReplicaConfig) -> DataResult<()> {
+        self.configure_replicas(config).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::testing::instance;
+    use std::sync::Mutex;
+
+    fn search_instance(nodes: i32) -> DatabaseInstance {
+        let mut instance = instance("unused", 0);
+        instance.id = "search-logs".into();
+        instance.engine = DatabaseEngine::Elasticsearch;
+        instance.tags.insert(NODE_COUNT_TAG.into(), nodes.to_string());
+        instance
+    }
+
+    #[test]
+    fn test_stats_mapping_and_watermarks() {
+        let cluster_stats = json!({
+            "nodes": {
+                "process": { "cpu": { "percent": 37 } },
+                "jvm": { "mem": { "heap_used_in_bytes": 750, "heap_max_in_bytes": 1000 } },
+                "fs": { "total_in_bytes": 2000, "available_in_bytes": 600 },
+            }
+        });
+        let node_stats = json!({
+            "nodes": {
+                "a": {
+                    "fs": { "total": { "total_in_bytes": 1000, "available_in_bytes": 500 } },
+                    "http": { "current_open": 4 },
+                    "indices": { "search": { "query_total": 10, "query_time_in_millis": 50 } },
+                },
+                "b": {
+                    "fs": { "total": { "total_in_bytes": 1000, "available_in_bytes": 80 } },
+                    "http": { "current_open": 2 },
+                    "indices": { "search": { "query_total": 30, "query_time_in_millis": 270 } },
+                },
+            }
+        });
+        let settings = json!({
+            "persistent": { "cluster.routing.allocation.disk.watermark.high": "0.95" },
+            "transient": { "cluster.routing.allocation.disk.watermark.low": "80%" },
+            "defaults": {
+                "cluster.routing.allocation.disk.watermark.low": "85%",
+                "cluster.routing.allocation.disk.watermark.high": "90%",
+                "cluster.routing.allocation.disk.watermark.flood_stage": "97%",
+            }
+        });
+        let watermarks = DiskWatermarks::from_settings(&settings);
+        assert_eq!(watermarks, DiskWatermarks { low: 80.0, high: 95.0, flood_stage: 97.0 });
+        // Ratios and absolute values do not confuse the parser
+        assert_eq!(
+            DiskWatermarks::from_settings(&json!({ "defaults": { "cluster.routing.allocation.disk.watermark.low": "500mb" } })),
+            DiskWatermarks::default()
+        );
+
+        let metrics = metrics_from_stats("search-logs", &cluster_stats, &node_stats, &watermarks, Utc::now());
+        assert_eq!(metrics.cpu_utilization, 37.0);
+        assert_eq!(metrics.memory_utilization, 75.0);
+        assert_eq!(metrics.disk_utilization, 70.0);
+        assert_eq!(metrics.connections, 6);
+        assert_eq!(metrics.latency_ms, 8.0);
+        // Node b is 92% full: past the 80% low watermark, short of the 95% high one
+        assert_eq!(metrics.disk_watermark, Some(DiskWatermark::Low));
+        assert_eq!(DiskWatermarks::default().status(92.0), DiskWatermark::High);
+        assert_eq!(DiskWatermarks::default().status(99.0), DiskWatermark::FloodStage);
+
+        let empty = metrics_from_stats("search-logs", &json!({}), &json!({}), &watermarks, Utc::now());
+        assert_eq!((empty.memory_utilization, empty.disk_watermark), (0.0, None));
+    }
+
+    // Answers from canned JSON by path prefix and records every call
+    #[derive(Default)]
+    struct FakeCluster {
+        responses: Mutex<Vec<(String, Value)>>,
+        calls: Mutex<Vec<(SearchMethod, String, Option<Value>)>>,
+        manifests: Mutex<Vec<Value>>,
+    }
+
+    impl FakeCluster {
+        fn respond(&self, prefix: &str, value: Value) {
+            self.responses.lock().unwrap().insert(0, (prefix.to_string(), value));
+        }
+
+        fn calls(&self, method: SearchMethod, prefix: &str) -> Vec<Option<Value>> {
+            self.calls
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|(m, p, _)| *m == method && p.starts_with(prefix))
+                .map(|(_, _, body)| body.clone())
+                .collect()
+        }
+
+        fn statefulset_replicas(&self) -> Vec<i64> {
+            self.manifests
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|m| m["kind"] == "StatefulSet")
+                .map(|m| m["spec"]["replicas"].as_i64().unwrap())
+                .collect()
+        }
+    }
+
+    #[async_trait]
+    impl SearchApi for FakeCluster {
+        async fn request(&self, _instance: &DatabaseInstance, method: SearchMethod, path: &str, body: Option<Value>) -> DataResult<Value> {
+            self.calls.lock().unwrap().push((method, path.to_string(), body));
+            let responses = self.responses.lock().unwrap();
+            Ok(responses
+                .iter()
+                .find(|(prefix, _)| method == SearchMethod::Get && path.starts_with(prefix.as_str()))
+                .map(|(_, value)| value.clone())
+                .unwrap_or(json!({ "acknowledged": true })))
+        }
+    }
+
+    #[async_trait]
+    impl ManifestApplier for FakeCluster {
+        async fn apply(&self, manifest: &Value) -> DataResult<()> {
+            self.manifests.lock().unwrap().push(manifest.clone());
+            Ok(())
+        }
+
+        async fn delete(&self, _kind: &str, _namespace: &str, _name: &str) -> DataResult<()> {
+            Ok(())
+        }
+    }
+
+    fn health(replicas: i32) -> Value {
+        json!({
+            "status": "green",
+            "number_of_nodes": 3,
+            "indices": {
+                "logs-2026.10": { "status": "green", "number_of_shards": 3, "number_of_replicas": replicas, "active_shards": 3 * (replicas + 1), "unassigned_shards": 0 },
+                "metrics": { "status": "green", "number_of_shards": 1, "number_of_replicas": 1, "active_shards": 2, "unassigned_shards": 0 },
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn test_scale_in_guard_and_drain() {
+        let cluster = Arc::new(FakeCluster::default());
+        let manager = OpenSearchManager::new(cluster.clone(), cluster.clone(), OpenSearchConfig::default())
+            .with_poll_interval(Duration::from_millis(1));
+        let created = manager.create_instance(search_instance(3)).await.unwrap();
+        assert_eq!(created.tags[TOPOLOGY_TAG], MULTI_NODE);
+        assert_eq!(created.endpoint, "search-logs.sirsi-data.svc");
+        let statefulset = cluster.manifests.lock().unwrap()[1].clone();
+        let env = statefulset["spec"]["template"]["spec"]["containers"][0]["env"].as_array().unwrap().clone();
+        assert!(env.contains(&json!({ "name": "cluster.initial_cluster_manager_nodes", "value": "search-logs-0,search-logs-1,search-logs-2" })));
+
+        // Two replicas need three nodes, so two nodes is refused before anything changes
+        cluster.respond("_cluster/health", health(2));
+        let refused = manager.scale_nodes("search-logs", 2).await;
+        assert!(matches!(refused, Err(DataError::Conflict(msg)) if msg.contains("logs-2026.10")));
+        assert!(cluster.calls(SearchMethod::Put, "_cluster/settings").is_empty());
+        assert_eq!(cluster.statefulset_replicas(), [3]);
+        assert!(check_scale_in(1, &index_health(&health(0))).is_err());
+
+        // One replica fits on two nodes; search-logs-2 is drained before the StatefulSet shrinks
+        cluster.respond("_cluster/health", health(1));
+        cluster.respond("_cat/shards", json!([{ "index": "metrics", "shard": "0", "node": "search-logs-2" }]));
+        let scale = manager.scale_nodes("search-logs", 2);
+        let moved = async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            cluster.respond("_cat/shards", json!([{ "index": "metrics", "shard": "0", "node": "search-logs-0" }]));
+        };
+        let (scaled, _) = tokio::join!(scale, moved);
+        assert_eq!(node_count(&scaled.unwrap()).unwrap(), 2);
+        assert_eq!(cluster.statefulset_replicas(), [3, 2]);
+        let exclusions = cluster.calls(SearchMethod::Put, "_cluster/settings");
+        assert_eq!(exclusions[0], Some(json!({ "persistent": { ALLOCATION_EXCLUDE_SETTING: "search-logs-2" } })));
+        assert_eq!(exclusions[1], Some(json!({ "persistent": { ALLOCATION_EXCLUDE_SETTING: null } })));
+        assert_eq!(cluster.calls(SearchMethod::Post, "_cluster/voting_config_exclusions?node_names=search-logs-2").len(), 1);
+
+        let details = manager.get_engine_details("search-logs").await.unwrap();
+        assert_eq!(details.indices.iter().map(|i| i.name.as_str()).collect::<Vec<_>>(), ["logs-2026.10", "metrics"]);
+        assert_eq!(details.indices[0].replicas, 1);
+
+        // Single-node clusters never grow
+        let mut single = search_instance(1);
+        single.id = "search-dev".into();
+        manager.create_instance(single).await.unwrap();
+        assert!(matches!(manager.scale_nodes("search-dev", 3).await, Err(DataError::Validation(_))));
+    }
+
+    // Needs a single-node OpenSearch with a filesystem snapshot path, e.g.
+    // `docker run -p 9200:9200 -e discovery.type=single-node -e DISABLE_SECURITY_PLUGIN=true
+    //  -e path.repo=/tmp/snapshots opensearchproject/opensearch:2.11.1`
+    // with SIRSI_TEST_OPENSEARCH_URL=localhost:9200
+    #[cfg(feature = "opensearch-tests")]
+    mod container {
+        use super::*;
+
+        #[derive(Default)]
+        struct NoManifests;
+
+        #[async_trait]
+        impl ManifestApplier for NoManifests {
+            async fn apply(&self, _manifest: &Value) -> DataResult<()> {
+                Ok(())
+            }
+
+            async fn delete(&self, _kind: &str, _namespace: &str, _name: &str) -> DataResult<()> {
+                Ok(())
+            }
+        }
+
+        #[tokio::test]
+        async fn test_metrics_details_and_snapshot_round_trip() {
+            let address = std::env::var("SIRSI_TEST_OPENSEARCH_URL").unwrap_or_else(|_| "localhost:9200".into());
+            let (host, port) = address.rsplit_once(':').unwrap();
+            let api = Arc::new(HttpSearchApi::new());
+            let config = OpenSearchConfig {
+                snapshot_repository_type: "fs".into(),
+                snapshot_settings: HashMap::from([("location".to_string(), "/tmp/snapshots".to_string())]),
+                ..OpenSearchConfig::default()
+            };
+            let manager = OpenSearchManager::new(api.clone(), Arc::new(NoManifests), config)
+                .with_poll_interval(Duration::from_millis(200));
+            let mut cluster = manager.create_instance(search_instance(1)).await.unwrap();
+            // Point at the container instead of the in-cluster service
+            cluster.endpoint = host.to_string();
+            cluster.port = port.parse().unwrap();
+            manager.store(&cluster).await;
+
+            let index = format!("sirsi-test-{}", uuid::Uuid::new_v4().simple());
+            api.request(&cluster, SearchMethod::Put, &index, Some(json!({ "settings": { "number_of_replicas": 0 } })))
+                .await
+                .unwrap();
+            api.request(&cluster, SearchMethod::Post, &format!("{}/_doc?refresh=true", index), Some(json!({ "msg": "hello" })))
+                .await
+                .unwrap();
+
+            let metrics = manager.get_metrics(&cluster.id, chrono::Duration::minutes(5)).await.unwrap();
+            assert!(metrics[0].memory_utilization > 0.0);
+            assert!(metrics[0].disk_watermark.is_some());
+            let details = manager.get_engine_details(&cluster.id).await.unwrap();
+            assert!(details.indices.iter().any(|i| i.name == index && i.status == HealthStatus::Green));
+
+            let backup = manager.create_backup(&cluster.id).await.unwrap();
+            let mut job = backup.clone();
+            for _ in 0..50 {
+                job = manager.refresh_backup(&backup.id).await.unwrap();
+                if !matches!(job.status, BackupStatus::InProgress) {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(200)).await;
+            }
+            assert!(matches!(job.status, BackupStatus::Completed));
+
+            api.request(&cluster, SearchMethod::Delete, &index, None).await.unwrap();
+            let restored = manager.restore_backup(&backup.id, &cluster.id).await.unwrap();
+            assert!(matches!(restored.status, InstanceStatus::Restoring));
+            let mut found = false;
+            for _ in 0..50 {
+                if let Ok(count) = api.request(&cluster, SearchMethod::Get, &format!("{}/_count", index), None).await {
+                    if count["count"] == 1 {
+                        found = true;
+                        break;
+                    }
+                }
+                tokio::time::sleep(Duration::from_millis(200)).await;
+            }
+            assert!(found);
+            api.request(&cluster, SearchMethod::Delete, &index, None).await.unwrap();
+        }
+    }
+}
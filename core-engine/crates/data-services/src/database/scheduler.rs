@@ -0,0 +1,783 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::cache::{self, CacheManager, ParameterGroup, ParameterManager};
+use crate::error::{DataError, DataResult};
+use super::{
+    DatabaseInstance, DatabaseManager, MaintenanceManager, MaintenanceStatus, MaintenanceTask, MaintenanceType,
+    MaintenanceWindow,
+};
+
+// Role a caller needs to run a deferred task before its window opens
+pub const MAINTENANCE_OVERRIDE_ROLE: &str = "maintenance-override";
+pub const DEFAULT_TASK_TIMEOUT_SECONDS: u64 = 1800;
+
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DeferredOperation {
+    ModifyInstance { instance: DatabaseInstance },
+    RestartInstance { instance_id: String },
+    // Parameter groups are shared, so the change waits for the window of the cluster it was made for
+    ModifyParameterGroup { cluster_id: String, group: ParameterGroup },
+}
+
+impl DeferredOperation {
+    // The database or cache cluster whose maintenance window applies
+    pub fn resource_id(&self) -> &str {
+        match self {
+            Self::ModifyInstance { instance } => &instance.id,
+            Self::RestartInstance { instance_id } => instance_id,
+            Self::ModifyParameterGroup { cluster_id, .. } => cluster_id,
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Self::ModifyInstance { instance } => format!("Modify {}", instance.id),
+            Self::RestartInstance { instance_id } => format!("Restart {}", instance_id),
+            Self::ModifyParameterGroup { cluster_id, group } => {
+                format!("Apply parameter group {} to {}", group.name, cluster_id)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Disruption {
+    Online,
+    Disruptive,
+}
+
+// Changes that need the instance to restart or fail over; tags, security groups and backup
+// settings apply online
+pub fn classify_instance_change(current: &DatabaseInstance, desired: &DatabaseInstance) -> Disruption {
+    let disruptive = current.engine != desired.engine
+        || current.version != desired.version
+        || current.size != desired.size
+        || current.storage_gb != desired.storage_gb
+        || current.port != desired.port
+        || current.network_id != desired.network_id;
+    if disruptive {
+        Disruption::Disruptive
+    } else {
+        Disruption::Online
+    }
+}
+
+// Parameter values are only read at node start, so any change to them waits for the window
+pub fn classify_parameter_change(current: &ParameterGroup, desired: &ParameterGroup) -> Disruption {
+    let values = |group: &ParameterGroup| -> HashMap<String, String> {
+        group.parameters.iter().map(|(k, p)| (k.clone(), p.value.clone())).collect()
+    };
+    if current.family != desired.family || values(current) != values(desired) {
+        Disruption::Disruptive
+    } else {
+        Disruption::Online
+    }
+}
+
+// Where the scheduler looks up windows and runs operations
+#[async_trait]
+pub trait OperationExecutor: Send + Sync {
+    async fn maintenance_window(&self, operation: &DeferredOperation) -> DataResult<MaintenanceWindow>;
+    async fn classify(&self, operation: &DeferredOperation) -> DataResult<Disruption>;
+    async fn execute(&self, operation: &DeferredOperation) -> DataResult<()>;
+}
+
+// Runs operations against the database and cache managers
+pub struct ManagedOperations {
+    databases: Arc<dyn DatabaseManager>,
+    caches: Option<Arc<dyn CacheManager>>,
+    parameters: Option<Arc<dyn ParameterManager>>,
+}
+
+impl ManagedOperations {
+    pub fn new(databases: Arc<dyn DatabaseManager>) -> Self {
+        Self {
+            databases,
+            caches: None,
+            parameters: None,
+        }
+    }
+
+    pub fn with_caches(mut self, caches: Arc<dyn CacheManager>, parameters: Arc<dyn ParameterManager>) -> Self {
+        self.caches = Some(caches);
+        self.parameters = Some(parameters);
+        self
+    }
+
+    fn caches(&self) -> DataResult<&Arc<dyn CacheManager>> {
+        self.caches
+            .as_ref()
+            .ok_or_else(|| DataError::Config("Parameter group changes need a cache manager".into()))
+    }
+
+    fn parameters(&self) -> DataResult<&Arc<dyn ParameterManager>> {
+        self.parameters
+            .as_ref()
+            .ok_or_else(|| DataError::Config("Parameter group changes need a parameter manager".into()))
+    }
+}
+
+fn cache_window(window: &cache::MaintenanceWindow) -> MaintenanceWindow {
+    MaintenanceWindow {
+        day: window.day,
+        start_time: window.start_time,
+        duration_hours: window.duration_hours,
+    }
+}
+
+#[async_trait]
+impl OperationExecutor for ManagedOperations {
+    async fn maintenance_window(&self, operation: &DeferredOperation) -> DataResult<MaintenanceWindow> {
+        match operation {
+            DeferredOperation::ModifyInstance { .. } | DeferredOperation::RestartInstance { .. } => Ok(self
+                .databases
+                .get_instance(operation.resource_id())
+                .await?
+                .maintenance_window),
+            DeferredOperation::ModifyParameterGroup { cluster_id, .. } => {
+                Ok(cache_window(&self.caches()?.get_cluster(cluster_id).await?.maintenance_window))
+            }
+        }
+    }
+
+    async fn classify(&self, operation: &DeferredOperation) -> DataResult<Disruption> {
+        match operation {
+            DeferredOperation::ModifyInstance { instance } => {
+                let current = self.databases.get_instance(&instance.id).await?;
+                Ok(classify_instance_change(&current, instance))
+            }
+            DeferredOperation::RestartInstance { .. } => Ok(Disruption::Disruptive),
+            DeferredOperation::ModifyParameterGroup { group, .. } => {
+                let current = self.parameters()?.get_parameter_group(&group.name).await?;
+                Ok(classify_parameter_change(&current, group))
+            }
+        }
+    }
+
+    async fn execute(&self, operation: &DeferredOperation) -> DataResult<()> {
+        match operation {
+            DeferredOperation::ModifyInstance { instance } => {
+                self.databases.modify_instance(instance.clone()).await.map(|_| ())
+            }
+            DeferredOperation::RestartInstance { instance_id } => self.databases.restart_instance(instance_id).await,
+            DeferredOperation::ModifyParameterGroup { group, .. } => {
+                self.parameters()?.modify_parameter_group(group.clone()).await.map(|_| ())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SubmitOptions {
+    pub description: Option<String>,
+    pub depends_on: Vec<String>,
+    pub timeout: Option<Duration>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Submission {
+    Applied,
+    Deferred(Box<MaintenanceTask>),
+}
+
+#[derive(Debug, Clone)]
+pub struct MaintenanceActor {
+    pub principal: String,
+    pub roles: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WindowSummary {
+    pub resource_id: String,
+    pub window_start: Option<DateTime<Utc>>,
+    pub window_end: Option<DateTime<Utc>>,
+    pub completed: Vec<String>,
+    pub failed: Vec<String>,
+    // Still scheduled, for the next occurrence of the window
+    pub carried_over: Vec<String>,
+}
+
+#[async_trait]
+pub trait MaintenanceNotifier: Send + Sync {
+    async fn window_finished(&self, summary: &WindowSummary) -> DataResult<()>;
+}
+
+// Defers disruptive operations submitted outside a resource's maintenance window and runs them
+// when it opens. Tasks only start if their timeout fits in what is left of the window; the rest
+// carry over to the next occurrence in their original order.
+pub struct MaintenanceScheduler {
+    executor: Arc<dyn OperationExecutor>,
+    clock: Arc<dyn Clock>,
+    notifier: Option<Arc<dyn MaintenanceNotifier>>,
+    override_role: String,
+    default_timeout: Duration,
+    // Submission order, which is also the run order among tasks that are ready
+    tasks: RwLock<Vec<MaintenanceTask>>,
+}
+
+impl MaintenanceScheduler {
+    pub fn new(executor: Arc<dyn OperationExecutor>) -> Self {
+        Self {
+            executor,
+            clock: Arc::new(SystemClock),
+            notifier: None,
+            override_role: MAINTENANCE_OVERRIDE_ROLE.to_string(),
+            default_timeout: Duration::from_secs(DEFAULT_TASK_TIMEOUT_SECONDS),
+            tasks: RwLock::new(Vec::new()),
+        }
+    }
+
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn with_notifier(mut self, notifier: Arc<dyn MaintenanceNotifier>) -> Self {
+        self.notifier = Some(notifier);
+        self
+    }
+
+    pub fn with_override_role(mut self, role: impl Into<String>) -> Self {
+        self.override_role = role.into();
+        self
+    }
+
+    pub fn with_default_timeout(mut self, timeout: Duration) -> Self {
+        self.default_timeout = timeout;
+        self
+    }
+
+    fn timeout(&self, task: &MaintenanceTask) -> Duration {
+        task.timeout_seconds.map(Duration::from_secs).unwrap_or(self.default_timeout)
+    }
+
+    async fn task(&self, id: &str) -> DataResult<MaintenanceTask> {
+        self.tasks
+            .read()
+            .await
+            .iter()
+            .find(|t| t.id == id)
+            .cloned()
+            .ok_or_else(|| DataError::NotFound(format!("Maintenance task {} not found", id)))
+    }
+
+    async fn update(&self, task: &MaintenanceTask) {
+        if let Some(stored) = self.tasks.write().await.iter_mut().find(|t| t.id == task.id) {
+            *stored = task.clone();
+        }
+    }
+
+    async fn check_dependencies(&self, depends_on: &[String]) -> DataResult<()> {
+        let tasks = self.tasks.read().await;
+        for id in depends_on {
+            if !tasks.iter().any(|t| &t.id == id) {
+                return Err(DataError::Validation(format!("Unknown dependency {}", id)));
+            }
+        }
+        Ok(())
+    }
+
+    // Online operations run immediately. Disruptive ones run immediately only inside the window
+    // with nothing left to wait for; otherwise they are queued for the window.
+    pub async fn submit(&self, operation: DeferredOperation, options: SubmitOptions) -> DataResult<Submission> {
+        self.check_dependencies(&options.depends_on).await?;
+        let timeout = options.timeout.unwrap_or(self.default_timeout);
+        let now = self.clock.now();
+        let (start, end) = self.executor.maintenance_window(&operation).await?.next_occurrence(now);
+        let blocked = !options.depends_on.is_empty() || self.has_queued(operation.resource_id()).await;
+
+        let disruption = self.executor.classify(&operation).await?;
+        let in_window = start <= now && now + chrono_duration(timeout) <= end;
+        if disruption == Disruption::Online || (in_window && !blocked) {
+            tokio::time::timeout(timeout, self.executor.execute(&operation))
+                .await
+                .map_err(|_| DataError::Unavailable(format!("{} timed out", operation.describe())))??;
+            return Ok(Submission::Applied);
+        }
+
+        let task = MaintenanceTask {
+            id: uuid::Uuid::new_v4().to_string(),
+            instance_id: operation.resource_id().to_string(),
+            task_type: MaintenanceType::Configuration,
+            status: MaintenanceStatus::Scheduled,
+            scheduled_at: start,
+            started_at: None,
+            completed_at: None,
+            description: options.description.unwrap_or_else(|| operation.describe()),
+            silence_alerts: false,
+            silence_id: None,
+            operation: Some(operation),
+            depends_on: options.depends_on,
+            timeout_seconds: Some(timeout.as_secs()),
+            error: None,
+        };
+        info!("Deferred {} on {} to the window at {}", task.description, task.instance_id, start);
+        self.tasks.write().await.push(task.clone());
+        Ok(Submission::Deferred(Box::new(task)))
+    }
+
+    // Work already queued for a resource goes first, even once its window is open
+    async fn has_queued(&self, resource_id: &str) -> bool {
+        self.tasks
+            .read()
+            .await
+            .iter()
+            .any(|t| t.instance_id == resource_id && matches!(t.status, MaintenanceStatus::Scheduled))
+    }
+
+    // One scheduler pass over every task whose window is open
+    pub async fn run_due(&self) -> DataResult<Vec<WindowSummary>> {
+        let now = self.clock.now();
+        let due: Vec<MaintenanceTask> = self
+            .tasks
+            .read()
+            .await
+            .iter()
+            .filter(|t| matches!(t.status, MaintenanceStatus::Scheduled) && t.scheduled_at <= now)
+            .cloned()
+            .collect();
+        if due.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut windows: HashMap<String, (DateTime<Utc>, DateTime<Utc>)> = HashMap::new();
+        let mut summaries: Vec<WindowSummary> = Vec::new();
+        for task in &due {
+            if windows.contains_key(&task.instance_id) {
+                continue;
+            }
+            let Some(operation) = &task.operation else { continue };
+            let window = self.executor.maintenance_window(operation).await?.next_occurrence(now);
+            windows.insert(task.instance_id.clone(), window);
+            summaries.push(WindowSummary {
+                resource_id: task.instance_id.clone(),
+                window_start: Some(window.0),
+                window_end: Some(window.1),
+                ..WindowSummary::default()
+            });
+        }
+
+        let mut pending: Vec<MaintenanceTask> = due;
+        loop {
+            let statuses: HashMap<String, MaintenanceStatus> =
+                self.tasks.read().await.iter().map(|t| (t.id.clone(), t.status.clone())).collect();
+            let dependency_state = |task: &MaintenanceTask| -> Option<Result<(), String>> {
+                for id in &task.depends_on {
+                    match statuses.get(id) {
+                        Some(MaintenanceStatus::Completed) => {}
+                        Some(MaintenanceStatus::Failed) | Some(MaintenanceStatus::Cancelled) | None => {
+                            return Some(Err(format!("Dependency {} did not complete", id)));
+                        }
+                        _ => return None,
+                    }
+                }
+                Some(Ok(()))
+            };
+            let Some(position) = pending.iter().position(|t| dependency_state(t).is_some()) else {
+                break;
+            };
+            let mut task = pending.remove(position);
+            let dependencies = dependency_state(&task).unwrap_or(Ok(()));
+            let Some(&(start, end)) = windows.get(&task.instance_id) else { continue };
+            let summary = summaries.iter_mut().find(|s| s.resource_id == task.instance_id);
+
+            if let Err(reason) = dependencies {
+                task.status = MaintenanceStatus::Failed;
+                task.completed_at = Some(self.clock.now());
+                task.error = Some(reason);
+                self.update(&task).await;
+                if let Some(summary) = summary {
+                    summary.failed.push(task.id.clone());
+                }
+                continue;
+            }
+
+            let now = self.clock.now();
+            let timeout = self.timeout(&task);
+            if start > now || now + chrono_duration(timeout) > end {
+                // Not enough window left; later tasks for this resource wait behind this one
+                let mut carried = vec![task];
+                let (rest, others): (Vec<_>, Vec<_>) =
+                    pending.into_iter().partition(|t| t.instance_id == carried[0].instance_id);
+                carried.extend(rest);
+                pending = others;
+                let next = carry_over_to(start, now);
+                for mut task in carried {
+                    task.scheduled_at = next;
+                    self.update(&task).await;
+                    if let Some(summary) = summaries.iter_mut().find(|s| s.resource_id == task.instance_id) {
+                        summary.carried_over.push(task.id.clone());
+                    }
+                }
+                continue;
+            }
+
+            task.status = MaintenanceStatus::InProgress;
+            task.started_at = Some(now);
+            self.update(&task).await;
+            let operation = task.operation.clone();
+            let result = match &operation {
+                Some(operation) => tokio::time::timeout(timeout, self.executor.execute(operation))
+                    .await
+                    .unwrap_or_else(|_| Err(DataError::Unavailable(format!("Timed out after {}s", timeout.as_secs())))),
+                None => Err(DataError::Validation("Task has no operation to run".into())),
+            };
+            task.completed_at = Some(self.clock.now());
+            let summary = summaries.iter_mut().find(|s| s.resource_id == task.instance_id);
+            match result {
+                Ok(()) => {
+                    task.status = MaintenanceStatus::Completed;
+                    if let Some(summary) = summary {
+                        summary.completed.push(task.id.clone());
+                    }
+                }
+                Err(e) => {
+                    warn!("Maintenance task {} on {} failed: {}", task.id, task.instance_id, e);
+                    task.status = MaintenanceStatus::Failed;
+                    task.error = Some(e.to_string());
+                    if let Some(summary) = summary {
+                        summary.failed.push(task.id.clone());
+                    }
+                }
+            }
+            self.update(&task).await;
+        }
+
+        // Whatever is left waits on a dependency queued behind another resource's window
+        for mut task in pending {
+            if let Some(&(start, _)) = windows.get(&task.instance_id) {
+                task.scheduled_at = carry_over_to(start, now);
+                self.update(&task).await;
+                if let Some(summary) = summaries.iter_mut().find(|s| s.resource_id == task.instance_id) {
+                    summary.carried_over.push(task.id.clone());
+                }
+            }
+        }
+
+        for summary in &summaries {
+            info!(
+                "Maintenance window for {}: {} completed, {} failed, {} carried over",
+                summary.resource_id,
+                summary.completed.len(),
+                summary.failed.len(),
+                summary.carried_over.len()
+            );
+            if let Some(notifier) = &self.notifier {
+                if let Err(e) = notifier.window_finished(summary).await {
+                    warn!("Failed to publish maintenance summary for {}: {}", summary.resource_id, e);
+                }
+            }
+        }
+        Ok(summaries)
+    }
+
+    // Emergency override: runs a deferred task now, regardless of its window
+    pub async fn apply_now(&self, task_id: &str, actor: &MaintenanceActor) -> DataResult<MaintenanceTask> {
+        if !actor.roles.iter().any(|r| r == &self.override_role) {
+            return Err(DataError::AccessDenied(format!(
+                "{} needs the {} role to apply maintenance outside its window",
+                actor.principal, self.override_role
+            )));
+        }
+        let mut task = self.task(task_id).await?;
+        if !matches!(task.status, MaintenanceStatus::Scheduled) {
+            return Err(DataError::Conflict(format!("Maintenance task {} is {:?}", task_id, task.status)));
+        }
+        let operation = task
+            .operation
+            .clone()
+            .ok_or_else(|| DataError::Validation(format!("Maintenance task {} has no operation to run", task_id)))?;
+        warn!("{} is applying maintenance task {} on {} outside its window", actor.principal, task_id, task.instance_id);
+        let timeout = self.timeout(&task);
+        task.status = MaintenanceStatus::InProgress;
+        task.started_at = Some(self.clock.now());
+        self.update(&task).await;
+        let result = tokio::time::timeout(timeout, self.executor.execute(&operation))
+            .await
+            .unwrap_or_else(|_| Err(DataError::Unavailable(format!("Timed out after {}s", timeout.as_secs()))));
+        task.completed_at = Some(self.clock.now());
+        match &result {
+            Ok(()) => task.status = MaintenanceStatus::Completed,
+            Err(e) => {
+                task.status = MaintenanceStatus::Failed;
+                task.error = Some(e.to_string());
+            }
+        }
+        self.update(&task).await;
+        result.map(|_| task)
+    }
+
+    pub fn spawn_scheduler(self: Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = self.run_due().await {
+                    warn!("Maintenance scheduler pass failed: {}", e);
+                }
+            }
+        })
+    }
+}
+
+fn chrono_duration(duration: Duration) -> chrono::Duration {
+    chrono::Duration::from_std(duration).unwrap_or(chrono::Duration::MAX)
+}
+
+// Windows recur weekly, so work carried out of an open window moves to the same time next week
+fn carry_over_to(start: DateTime<Utc>, now: DateTime<Utc>) -> DateTime<Utc> {
+    if start > now {
+        start
+    } else {
+        start + chrono::Duration::weeks(1)
+    }
+}
+
+#[async_trait]
+impl MaintenanceManager for MaintenanceScheduler {
+    // Tasks scheduled directly keep their own time, but still wait for the window to be open
+    async fn schedule_maintenance(&self, mut task: MaintenanceTask) -> DataResult<MaintenanceTask> {
+        if task.operation.is_none() {
+            return Err(DataError::Validation(format!("Maintenance task {} has no operation to run", task.id)));
+        }
+        self.check_dependencies(&task.depends_on).await?;
+        let mut tasks = self.tasks.write().await;
+        if tasks.iter().any(|t| t.id == task.id) {
+            return Err(DataError::Conflict(format!("Maintenance task {} already exists", task.id)));
+        }
+        task.status = MaintenanceStatus::Scheduled;
+        tasks.push(task.clone());
+        Ok(task)
+    }
+
+    async fn get_maintenance_task(&self, id: &str) -> DataResult<MaintenanceTask> {
+        self.task(id).await
+    }
+
+    async fn list_maintenance_tasks(&self, instance_id: &str) -> DataResult<Vec<MaintenanceTask>> {
+        Ok(self.tasks.read().await.iter().filter(|t| t.instance_id == instance_id).cloned().collect())
+    }
+
+    async fn cancel_maintenance_task(&self, id: &str) -> DataResult<()> {
+        let mut task = self.task(id).await?;
+        if !matches!(task.status, MaintenanceStatus::Scheduled) {
+            return Err(DataError::Conflict(format!("Maintenance task {} is {:?}", id, task.status)));
+        }
+        task.status = MaintenanceStatus::Cancelled;
+        self.update(&task).await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::testing::instance;
+    use chrono::{NaiveTime, TimeZone, Weekday};
+    use std::sync::Mutex;
+
+    struct ManualClock(Mutex<DateTime<Utc>>);
+
+    impl ManualClock {
+        fn set(&self, at: DateTime<Utc>) {
+            *self.0.lock().unwrap() = at;
+        }
+
+        fn advance(&self, by: chrono::Duration) {
+            *self.0.lock().unwrap() += by;
+        }
+    }
+
+    impl Clock for ManualClock {
+        fn now(&self) -> DateTime<Utc> {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    // Every operation takes `duration` of clock time and is logged as "<resource>:<what>"
+    struct Operations {
+        clock: Arc<ManualClock>,
+        current: DatabaseInstance,
+        windows: HashMap<String, MaintenanceWindow>,
+        duration: chrono::Duration,
+        log: Mutex<Vec<String>>,
+    }
+
+    impl Operations {
+        fn log(&self) -> Vec<String> {
+            self.log.lock().unwrap().clone()
+        }
+    }
+
+    #[async_trait]
+    impl OperationExecutor for Operations {
+        async fn maintenance_window(&self, operation: &DeferredOperation) -> DataResult<MaintenanceWindow> {
+            self.windows
+                .get(operation.resource_id())
+                .cloned()
+                .ok_or_else(|| DataError::NotFound(operation.resource_id().to_string()))
+        }
+
+        async fn classify(&self, operation: &DeferredOperation) -> DataResult<Disruption> {
+            Ok(match operation {
+                DeferredOperation::ModifyInstance { instance } => classify_instance_change(&self.current, instance),
+                _ => Disruption::Disruptive,
+            })
+        }
+
+        async fn execute(&self, operation: &DeferredOperation) -> DataResult<()> {
+            let what = match operation {
+                DeferredOperation::ModifyInstance { instance } => format!("size={}", instance.size),
+                DeferredOperation::RestartInstance { .. } => "restart".to_string(),
+                DeferredOperation::ModifyParameterGroup { group, .. } => format!("params={}", group.name),
+            };
+            self.log.lock().unwrap().push(format!("{}:{}", operation.resource_id(), what));
+            self.clock.advance(self.duration);
+            Ok(())
+        }
+    }
+
+    fn window(day: Weekday, duration_hours: i32) -> MaintenanceWindow {
+        MaintenanceWindow {
+            day,
+            start_time: NaiveTime::from_hms_opt(2, 0, 0).unwrap(),
+            duration_hours,
+        }
+    }
+
+    fn resize(size: &str) -> DeferredOperation {
+        let mut desired = instance("localhost", 5432);
+        desired.size = size.to_string();
+        DeferredOperation::ModifyInstance { instance: desired }
+    }
+
+    fn restart(id: &str) -> DeferredOperation {
+        DeferredOperation::RestartInstance { instance_id: id.to_string() }
+    }
+
+    fn after(ids: &[&MaintenanceTask]) -> SubmitOptions {
+        SubmitOptions {
+            depends_on: ids.iter().map(|t| t.id.clone()).collect(),
+            ..SubmitOptions::default()
+        }
+    }
+
+    fn deferred(submission: Submission) -> MaintenanceTask {
+        match submission {
+            Submission::Deferred(task) => *task,
+            Submission::Applied => panic!("expected the operation to be deferred"),
+        }
+    }
+
+    fn setup(windows: &[(&str, MaintenanceWindow)], duration_minutes: i64) -> (Arc<ManualClock>, Arc<Operations>, MaintenanceScheduler) {
+        // Friday 2024-03-01 09:00, outside every window used below
+        let clock = Arc::new(ManualClock(Mutex::new(Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap())));
+        let operations = Arc::new(Operations {
+            clock: clock.clone(),
+            current: instance("localhost", 5432),
+            windows: windows.iter().map(|(id, w)| (id.to_string(), w.clone())).collect(),
+            duration: chrono::Duration::minutes(duration_minutes),
+            log: Mutex::new(Vec::new()),
+        });
+        let scheduler = MaintenanceScheduler::new(operations.clone())
+            .with_clock(clock.clone())
+            .with_default_timeout(Duration::from_secs(25 * 60));
+        (clock, operations, scheduler)
+    }
+
+    #[tokio::test]
+    async fn test_disruptive_operations_wait_for_window_in_dependency_order() {
+        let sunday = Utc.with_ymd_and_hms(2024, 3, 3, 2, 0, 0).unwrap();
+        let (clock, operations, scheduler) = setup(
+            &[("db-orders", window(Weekday::Sun, 2)), ("db-billing", window(Weekday::Mon, 2))],
+            5,
+        );
+
+        // A tag change is online and applies straight away; a resize waits for Sunday
+        let mut tagged = instance("localhost", 5432);
+        tagged.tags.insert("team".into(), "payments".into());
+        let applied = scheduler.submit(DeferredOperation::ModifyInstance { instance: tagged }, SubmitOptions::default()).await.unwrap();
+        assert!(matches!(applied, Submission::Applied));
+        let resized = deferred(scheduler.submit(resize("large"), SubmitOptions::default()).await.unwrap());
+        assert_eq!(resized.scheduled_at, sunday);
+        let bounce = deferred(scheduler.submit(restart("db-orders"), after(&[&resized])).await.unwrap());
+        // Billing's window is Monday, so a task waiting on it cannot run on Sunday
+        let billing = deferred(scheduler.submit(restart("db-billing"), SubmitOptions::default()).await.unwrap());
+        let grouped = deferred(scheduler.submit(resize("xlarge"), after(&[&bounce, &billing])).await.unwrap());
+        assert!(scheduler.submit(restart("db-orders"), SubmitOptions { depends_on: vec!["nope".into()], ..Default::default() }).await.is_err());
+
+        assert!(scheduler.run_due().await.unwrap().is_empty());
+        assert_eq!(operations.log(), ["db-orders:size=small"]);
+
+        clock.set(sunday + chrono::Duration::minutes(1));
+        let summaries = scheduler.run_due().await.unwrap();
+        assert_eq!(operations.log(), ["db-orders:size=small", "db-orders:size=large", "db-orders:restart"]);
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(summaries[0].completed, [resized.id.clone(), bounce.id.clone()]);
+        assert_eq!(summaries[0].carried_over, [grouped.id.as_str()]);
+        let grouped = scheduler.get_maintenance_task(&grouped.id).await.unwrap();
+        assert_eq!(grouped.scheduled_at, sunday + chrono::Duration::weeks(1));
+
+        // Monday runs billing; the grouped resize then waits for next Sunday's window
+        clock.set(Utc.with_ymd_and_hms(2024, 3, 4, 2, 30, 0).unwrap());
+        scheduler.run_due().await.unwrap();
+        clock.set(sunday + chrono::Duration::weeks(1));
+        scheduler.run_due().await.unwrap();
+        assert_eq!(&operations.log()[3..], ["db-billing:restart", "db-orders:size=xlarge"]);
+        let statuses: Vec<_> = scheduler.list_maintenance_tasks("db-orders").await.unwrap().into_iter().map(|t| t.status).collect();
+        assert!(statuses.iter().all(|s| matches!(s, MaintenanceStatus::Completed)));
+    }
+
+    #[tokio::test]
+    async fn test_short_window_carries_over_and_override_needs_role() {
+        let sunday = Utc.with_ymd_and_hms(2024, 3, 3, 2, 0, 0).unwrap();
+        // One hour of window, 25-minute timeouts and operations that take all of them
+        let (clock, operations, scheduler) = setup(&[("db-orders", window(Weekday::Sun, 1))], 25);
+        let mut queued = Vec::new();
+        for operation in [resize("large"), restart("db-orders"), resize("xlarge")] {
+            queued.push(deferred(scheduler.submit(operation, SubmitOptions::default()).await.unwrap()));
+        }
+
+        clock.set(sunday);
+        let summary = scheduler.run_due().await.unwrap().remove(0);
+        // Two tasks fit (02:00-02:50); the third would overrun 03:00
+        assert_eq!(summary.completed, [queued[0].id.clone(), queued[1].id.clone()]);
+        assert_eq!(summary.carried_over, [queued[2].id.as_str()]);
+        assert_eq!(summary.window_end, Some(sunday + chrono::Duration::hours(1)));
+        let carried = scheduler.get_maintenance_task(&queued[2].id).await.unwrap();
+        assert!(matches!(carried.status, MaintenanceStatus::Scheduled));
+        assert_eq!(carried.scheduled_at, sunday + chrono::Duration::weeks(1));
+
+        // Emergencies can skip the wait, but only with the override role
+        let operator = MaintenanceActor { principal: "oncall".into(), roles: vec!["operator".into()] };
+        assert!(matches!(scheduler.apply_now(&carried.id, &operator).await, Err(DataError::AccessDenied(_))));
+        let admin = MaintenanceActor { principal: "oncall".into(), roles: vec![MAINTENANCE_OVERRIDE_ROLE.into()] };
+        let applied = scheduler.apply_now(&carried.id, &admin).await.unwrap();
+        assert!(matches!(applied.status, MaintenanceStatus::Completed));
+        assert_eq!(operations.log(), ["db-orders:size=large", "db-orders:restart", "db-orders:size=xlarge"]);
+        assert!(matches!(scheduler.apply_now(&carried.id, &admin).await, Err(DataError::Conflict(_))));
+        assert!(scheduler.run_due().await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,925 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgConnection;
+use sqlx::Connection;
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+
+use crate::cache::{self, CacheManager, CacheNode, ClusterStatus, NodeRole, RedisCommandAdmin};
+use crate::error::{DataError, DataResult};
+use super::cloning::BackupCatalog;
+use super::credentials::{quote_identifier, DatabaseCredentials};
+use super::{BackupJob, BackupStatus, DatabaseEngine, DatabaseInstance, DatabaseManager, InstanceStatus};
+
+pub const VERIFICATION_OF_TAG: &str = "sirsi:verification-of";
+pub const DEFAULT_SANDBOX_SIZE: &str = "small";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmokeQuery {
+    pub name: String,
+    pub sql: String,
+    pub min_rows: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerificationPolicy {
+    pub source_id: String,
+    pub engine: DatabaseEngine,
+    // Bounds cost: a verification is skipped if the last one started less than this long ago
+    pub min_interval_hours: i64,
+    // Alert once the last passing verification is older than this
+    pub max_age_hours: i64,
+    // Temporary instances get this size rather than the source's
+    pub sandbox_size: String,
+    pub smoke_queries: Vec<SmokeQuery>,
+    // Allowed relative difference between recorded and restored row or key counts
+    pub count_tolerance: f64,
+    pub created_at: DateTime<Utc>,
+}
+
+impl VerificationPolicy {
+    pub fn new(source_id: impl Into<String>, engine: DatabaseEngine) -> Self {
+        Self {
+            source_id: source_id.into(),
+            engine,
+            min_interval_hours: 24,
+            max_age_hours: 7 * 24,
+            sandbox_size: DEFAULT_SANDBOX_SIZE.to_string(),
+            smoke_queries: Vec::new(),
+            count_tolerance: 0.0,
+            created_at: Utc::now(),
+        }
+    }
+
+    pub fn with_smoke_query(mut self, name: impl Into<String>, sql: impl Into<String>, min_rows: u64) -> Self {
+        self.smoke_queries.push(SmokeQuery { name: name.into(), sql: sql.into(), min_rows });
+        self
+    }
+
+    pub fn with_sandbox_size(mut self, size: impl Into<String>) -> Self {
+        self.sandbox_size = size.into();
+        self
+    }
+
+    pub fn with_min_interval_hours(mut self, hours: i64) -> Self {
+        self.min_interval_hours = hours;
+        self
+    }
+
+    pub fn with_max_age_hours(mut self, hours: i64) -> Self {
+        self.max_age_hours = hours;
+        self
+    }
+
+    pub fn with_count_tolerance(mut self, tolerance: f64) -> Self {
+        self.count_tolerance = tolerance;
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum RestoredInstance {
+    Database(DatabaseInstance),
+    Cache { cluster_id: String, node: CacheNode },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CheckResult {
+    pub name: String,
+    pub passed: bool,
+    pub expected: Option<String>,
+    pub actual: Option<String>,
+    pub error: Option<String>,
+}
+
+impl CheckResult {
+    fn failed(name: impl Into<String>, error: impl ToString) -> Self {
+        Self { name: name.into(), passed: false, expected: None, actual: None, error: Some(error.to_string()) }
+    }
+
+    fn count(name: impl Into<String>, expected: u64, actual: u64, tolerance: f64) -> Self {
+        let allowed = (expected as f64 * tolerance).floor() as u64;
+        Self {
+            name: name.into(),
+            passed: expected.abs_diff(actual) <= allowed,
+            expected: Some(expected.to_string()),
+            actual: Some(actual.to_string()),
+            error: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VerificationOutcome {
+    Passed,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupVerificationResult {
+    pub id: String,
+    pub source_id: String,
+    pub backup_id: Option<String>,
+    pub temporary_instance_id: String,
+    pub outcome: VerificationOutcome,
+    pub checks: Vec<CheckResult>,
+    // Why the restore or validation couldn't run, if it didn't
+    pub error: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub completed_at: DateTime<Utc>,
+    pub restore_ms: u64,
+    pub validation_ms: u64,
+    pub teardown_ms: u64,
+    // False leaves a temporary instance running; it is reported like a failure
+    pub torn_down: bool,
+}
+
+// Creates and removes the temporary instances backups are restored into
+#[async_trait]
+pub trait RestoreSandbox: Send + Sync {
+    async fn restore(&self, source_id: &str, backup: &BackupJob, instance_id: &str, size: &str) -> DataResult<RestoredInstance>;
+    // Must succeed when the instance was never created, since failed restores are torn down too
+    async fn teardown(&self, instance_id: &str) -> DataResult<()>;
+}
+
+#[async_trait]
+pub trait RestoreProbe: Send + Sync {
+    async fn validate(
+        &self,
+        restored: &RestoredInstance,
+        backup: &BackupJob,
+        policy: &VerificationPolicy,
+    ) -> DataResult<Vec<CheckResult>>;
+}
+
+#[async_trait]
+pub trait VerificationStore: Send + Sync {
+    async fn save(&self, result: &BackupVerificationResult) -> DataResult<()>;
+    // Oldest first
+    async fn list(&self, source_id: &str) -> DataResult<Vec<BackupVerificationResult>>;
+}
+
+#[derive(Default)]
+pub struct InMemoryVerificationStore {
+    results: RwLock<HashMap<String, Vec<BackupVerificationResult>>>,
+}
+
+impl InMemoryVerificationStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl VerificationStore for InMemoryVerificationStore {
+    async fn save(&self, result: &BackupVerificationResult) -> DataResult<()> {
+        self.results.write().await.entry(result.source_id.clone()).or_default().push(result.clone());
+        Ok(())
+    }
+
+    async fn list(&self, source_id: &str) -> DataResult<Vec<BackupVerificationResult>> {
+        Ok(self.results.read().await.get(source_id).cloned().unwrap_or_default())
+    }
+}
+
+#[async_trait]
+pub trait VerificationNotifier: Send + Sync {
+    async fn verification_failed(&self, result: &BackupVerificationResult) -> DataResult<()>;
+    // `last_passed` is None when the source has never passed verification
+    async fn verification_stale(
+        &self,
+        policy: &VerificationPolicy,
+        last_passed: Option<&BackupVerificationResult>,
+    ) -> DataResult<()>;
+}
+
+fn tolerate_missing(result: DataResult<()>) -> DataResult<()> {
+    match result {
+        Err(DataError::NotFound(_)) => Ok(()),
+        other => other,
+    }
+}
+
+// Restores onto a new managed database instance copied from the source's config
+pub struct ManagedDatabaseSandbox {
+    instances: Arc<dyn DatabaseManager>,
+}
+
+impl ManagedDatabaseSandbox {
+    pub fn new(instances: Arc<dyn DatabaseManager>) -> Self {
+        Self { instances }
+    }
+}
+
+#[async_trait]
+impl RestoreSandbox for ManagedDatabaseSandbox {
+    async fn restore(&self, source_id: &str, backup: &BackupJob, instance_id: &str, size: &str) -> DataResult<RestoredInstance> {
+        let source = self.instances.get_instance(source_id).await?;
+        let now = Utc::now();
+        let mut config = source.clone();
+        config.id = instance_id.to_string();
+        config.name = instance_id.to_string();
+        // Storage stays as the source's so the restore fits
+        config.size = size.to_string();
+        config.status = InstanceStatus::Creating;
+        config.created_at = now;
+        config.updated_at = now;
+        config.tags.insert(VERIFICATION_OF_TAG.to_string(), source.id.clone());
+        let created = self.instances.create_instance(config).await?;
+        let restored = self.instances.restore_backup(&backup.id, &created.id).await?;
+        Ok(RestoredInstance::Database(restored))
+    }
+
+    async fn teardown(&self, instance_id: &str) -> DataResult<()> {
+        tolerate_missing(self.instances.delete_instance(instance_id).await)
+    }
+}
+
+// Restores onto a single-node cache cluster copied from the source's config
+pub struct ManagedCacheSandbox {
+    clusters: Arc<dyn CacheManager>,
+    backups: Arc<dyn cache::BackupManager>,
+}
+
+impl ManagedCacheSandbox {
+    pub fn new(clusters: Arc<dyn CacheManager>, backups: Arc<dyn cache::BackupManager>) -> Self {
+        Self { clusters, backups }
+    }
+}
+
+#[async_trait]
+impl RestoreSandbox for ManagedCacheSandbox {
+    async fn restore(&self, source_id: &str, backup: &BackupJob, instance_id: &str, size: &str) -> DataResult<RestoredInstance> {
+        let source = self.clusters.get_cluster(source_id).await?;
+        let now = Utc::now();
+        let mut config = source.clone();
+        config.id = instance_id.to_string();
+        config.name = instance_id.to_string();
+        config.node_type = size.to_string();
+        config.num_nodes = 1;
+        config.status = ClusterStatus::Creating;
+        config.created_at = now;
+        config.updated_at = now;
+        config.tags.insert(VERIFICATION_OF_TAG.to_string(), source.id.clone());
+        let created = self.clusters.create_cluster(config).await?;
+        self.backups.restore_backup(&backup.id, &created.id).await?;
+        let mut nodes = self.clusters.list_nodes(&created.id).await?;
+        if nodes.is_empty() {
+            return Err(DataError::Cache(format!("Restored cluster {} has no nodes", created.id)));
+        }
+        let primary = nodes.iter().position(|n| n.role == NodeRole::Primary).unwrap_or(0);
+        Ok(RestoredInstance::Cache { cluster_id: created.id, node: nodes.swap_remove(primary) })
+    }
+
+    async fn teardown(&self, instance_id: &str) -> DataResult<()> {
+        tolerate_missing(self.clusters.delete_cluster(instance_id).await)
+    }
+}
+
+// Compares restored table row counts with the backup manifest and runs the policy's smoke queries
+pub struct PostgresProbe {
+    admin: DatabaseCredentials,
+}
+
+impl PostgresProbe {
+    pub fn new(admin: DatabaseCredentials) -> Self {
+        Self { admin }
+    }
+}
+
+#[async_trait]
+impl RestoreProbe for PostgresProbe {
+    async fn validate(
+        &self,
+        restored: &RestoredInstance,
+        backup: &BackupJob,
+        policy: &VerificationPolicy,
+    ) -> DataResult<Vec<CheckResult>> {
+        let RestoredInstance::Database(instance) = restored else {
+            return Err(DataError::Validation("The Postgres probe can only validate database restores".into()));
+        };
+        let mut conn = PgConnection::connect_with(&self.admin.connect_options(instance)?)
+            .await
+            .map_err(|e| DataError::Database(format!("Failed to connect to restored instance {}: {}", instance.id, e)))?;
+
+        let mut checks = Vec::new();
+        let mut tables: Vec<(&String, &u64)> = backup.manifest.iter().flat_map(|m| &m.row_counts).collect();
+        tables.sort();
+        for (table, expected) in tables {
+            let name = format!("row_count:{}", table);
+            let quoted = table.split('.').map(quote_identifier).collect::<Vec<_>>().join(".");
+            let counted = sqlx::query_scalar::<_, i64>(&format!("SELECT count(*) FROM {}", quoted))
+                .fetch_one(&mut conn)
+                .await;
+            checks.push(match counted {
+                Ok(actual) => CheckResult::count(name, *expected, actual.max(0) as u64, policy.count_tolerance),
+                Err(e) => CheckResult::failed(name, e),
+            });
+        }
+        for query in &policy.smoke_queries {
+            let name = format!("smoke:{}", query.name);
+            checks.push(match sqlx::raw_sql(&query.sql).fetch_all(&mut conn).await {
+                Ok(rows) => CheckResult {
+                    passed: rows.len() as u64 >= query.min_rows,
+                    expected: Some(format!(">= {} rows", query.min_rows)),
+                    actual: Some(format!("{} rows", rows.len())),
+                    name,
+                    error: None,
+                },
+                Err(e) => CheckResult::failed(name, e),
+            });
+        }
+        let _ = conn.close().await;
+        Ok(checks)
+    }
+}
+
+// DBSIZE against the manifest's key count (or just non-empty without one), and that every
+// sampled key survived the restore
+pub struct RedisProbe {
+    admin: RedisCommandAdmin,
+}
+
+impl RedisProbe {
+    pub fn new(admin: RedisCommandAdmin) -> Self {
+        Self { admin }
+    }
+}
+
+#[async_trait]
+impl RestoreProbe for RedisProbe {
+    async fn validate(
+        &self,
+        restored: &RestoredInstance,
+        backup: &BackupJob,
+        policy: &VerificationPolicy,
+    ) -> DataResult<Vec<CheckResult>> {
+        let RestoredInstance::Cache { node, .. } = restored else {
+            return Err(DataError::Validation("The Redis probe can only validate cache restores".into()));
+        };
+        let manifest = backup.manifest.clone().unwrap_or_default();
+        let size: u64 = self.admin.query(node, &redis::cmd("DBSIZE")).await?;
+        let mut checks = vec![match manifest.key_count {
+            Some(expected) => CheckResult::count("dbsize", expected, size, policy.count_tolerance),
+            None => CheckResult {
+                name: "dbsize".into(),
+                passed: size > 0,
+                expected: Some("> 0".into()),
+                actual: Some(size.to_string()),
+                error: None,
+            },
+        }];
+        if !manifest.sample_keys.is_empty() {
+            let mut missing = Vec::new();
+            for key in &manifest.sample_keys {
+                if !self.admin.query::<bool>(node, redis::cmd("EXISTS").arg(key)).await? {
+                    missing.push(key.as_str());
+                }
+            }
+            checks.push(CheckResult {
+                name: "key_sample".into(),
+                passed: missing.is_empty(),
+                expected: Some(manifest.sample_keys.len().to_string()),
+                actual: Some((manifest.sample_keys.len() - missing.len()).to_string()),
+                error: (!missing.is_empty()).then(|| format!("Missing keys: {}", missing.join(", "))),
+            });
+        }
+        Ok(checks)
+    }
+}
+
+struct Claim<'a> {
+    active: &'a StdMutex<HashSet<String>>,
+    id: String,
+}
+
+impl Drop for Claim<'_> {
+    fn drop(&mut self) {
+        self.active.lock().unwrap().remove(&self.id);
+    }
+}
+
+struct Engine {
+    engine: DatabaseEngine,
+    sandbox: Arc<dyn RestoreSandbox>,
+    probe: Arc<dyn RestoreProbe>,
+}
+
+fn millis(since: Instant) -> u64 {
+    since.elapsed().as_millis() as u64
+}
+
+// Proves backups restore: the latest completed backup of a source goes onto a temporary
+// instance, gets validated and the instance is torn down again, pass or fail
+pub struct BackupVerifier {
+    backups: Arc<dyn BackupCatalog>,
+    store: Arc<dyn VerificationStore>,
+    engines: Vec<Engine>,
+    notifier: Option<Arc<dyn VerificationNotifier>>,
+    policies: RwLock<HashMap<String, VerificationPolicy>>,
+    active: StdMutex<HashSet<String>>,
+    // Per source, the last passing verification a staleness alert went out for
+    stale_alerted: StdMutex<HashMap<String, Option<String>>>,
+}
+
+impl BackupVerifier {
+    pub fn new(backups: Arc<dyn BackupCatalog>, store: Arc<dyn VerificationStore>) -> Self {
+        Self {
+            backups,
+            store,
+            engines: Vec::new(),
+            notifier: None,
+            policies: RwLock::new(HashMap::new()),
+            active: StdMutex::new(HashSet::new()),
+            stale_alerted: StdMutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn with_engine(mut self, engine: DatabaseEngine, sandbox: Arc<dyn RestoreSandbox>, probe: Arc<dyn RestoreProbe>) -> Self {
+        self.engines.retain(|e| e.engine != engine);
+        self.engines.push(Engine { engine, sandbox, probe });
+        self
+    }
+
+    pub fn with_notifier(mut self, notifier: Arc<dyn VerificationNotifier>) -> Self {
+        self.notifier = Some(notifier);
+        self
+    }
+
+    fn engine(&self, engine: &DatabaseEngine) -> DataResult<&Engine> {
+        self.engines
+            .iter()
+            .find(|e| &e.engine == engine)
+            .ok_or_else(|| DataError::Config(format!("No restore sandbox registered for {:?}", engine)))
+    }
+
+    pub async fn set_policy(&self, policy: VerificationPolicy) -> DataResult<VerificationPolicy> {
+        if policy.min_interval_hours <= 0 || policy.max_age_hours <= 0 {
+            return Err(DataError::Validation("Verification interval and maximum age must be positive".into()));
+        }
+        if !(0.0..1.0).contains(&policy.count_tolerance) {
+            return Err(DataError::Validation("Count tolerance must be at least 0 and below 1".into()));
+        }
+        self.engine(&policy.engine)?;
+        self.policies.write().await.insert(policy.source_id.clone(), policy.clone());
+        Ok(policy)
+    }
+
+    pub async fn get_policy(&self, source_id: &str) -> DataResult<VerificationPolicy> {
+        self.policies
+            .read()
+            .await
+            .get(source_id)
+            .cloned()
+            .ok_or_else(|| DataError::NotFound(format!("No verification policy for {}", source_id)))
+    }
+
+    pub async fn history(&self, source_id: &str) -> DataResult<Vec<BackupVerificationResult>> {
+        self.store.list(source_id).await
+    }
+
+    async fn due(&self, policy: &VerificationPolicy, now: DateTime<Utc>) -> DataResult<bool> {
+        let last = self.store.list(&policy.source_id).await?.pop();
+        Ok(last.is_none_or(|last| now - last.started_at >= chrono::Duration::hours(policy.min_interval_hours)))
+    }
+
+    // Throttled when the source was verified within its policy's minimum interval
+    pub async fn verify(&self, source_id: &str, now: DateTime<Utc>) -> DataResult<BackupVerificationResult> {
+        let policy = self.get_policy(source_id).await?;
+        if !self.active.lock().unwrap().insert(source_id.to_string()) {
+            return Err(DataError::Conflict(format!("Backups of {} are already being verified", source_id)));
+        }
+        let _claim = Claim { active: &self.active, id: source_id.to_string() };
+        if !self.due(&policy, now).await? {
+            return Err(DataError::Throttled(format!(
+                "Backups of {} were verified less than {} hours ago",
+                source_id, policy.min_interval_hours
+            )));
+        }
+        let engine = self.engine(&policy.engine)?;
+        let result = self.run(&policy, engine, now).await;
+        self.store.save(&result).await?;
+
+        if result.outcome == VerificationOutcome::Passed && result.torn_down {
+            info!("Backup {:?} of {} verified in {} ms", result.backup_id, source_id, result.restore_ms + result.validation_ms);
+            return Ok(result);
+        }
+        error!(
+            "Verification of {} failed (torn down: {}): {}",
+            source_id,
+            result.torn_down,
+            result.error.as_deref().unwrap_or("validation checks failed")
+        );
+        if let Some(notifier) = &self.notifier {
+            if let Err(e) = notifier.verification_failed(&result).await {
+                warn!("Failed to send verification failure alert for {}: {}", source_id, e);
+            }
+        }
+        Ok(result)
+    }
+
+    // For backup completion hooks: verifies unless the source isn't due yet or has no policy
+    pub async fn on_backup_completed(&self, source_id: &str, now: DateTime<Utc>) -> DataResult<Option<BackupVerificationResult>> {
+        match self.verify(source_id, now).await {
+            Ok(result) => Ok(Some(result)),
+            Err(DataError::Throttled(_) | DataError::NotFound(_) | DataError::Conflict(_)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn latest_backup(&self, source_id: &str) -> DataResult<BackupJob> {
+        self.backups
+            .list_backups(source_id)
+            .await?
+            .into_iter()
+            .filter(|b| matches!(b.status, BackupStatus::Completed))
+            .max_by_key(|b| b.completed_at.unwrap_or(b.started_at))
+            .ok_or_else(|| DataError::NotFound(format!("Instance {} has no completed backup to verify", source_id)))
+    }
+
+    // Failures of any step end up in the result rather than as an error
+    async fn run(&self, policy: &VerificationPolicy, engine: &Engine, now: DateTime<Utc>) -> BackupVerificationResult {
+        let started = Instant::now();
+        let mut result = BackupVerificationResult {
+            id: uuid::Uuid::new_v4().to_string(),
+            source_id: policy.source_id.clone(),
+            backup_id: None,
+            temporary_instance_id: format!("{}-verify-{}", policy.source_id, &uuid::Uuid::new_v4().simple().to_string()[..8]),
+            outcome: VerificationOutcome::Failed,
+            checks: Vec::new(),
+            error: None,
+            started_at: now,
+            completed_at: now,
+            restore_ms: 0,
+            validation_ms: 0,
+            teardown_ms: 0,
+            torn_down: true,
+        };
+        let backup = match self.latest_backup(&policy.source_id).await {
+            Ok(backup) => backup,
+            Err(e) => {
+                result.error = Some(e.to_string());
+                result.completed_at = now + chrono::Duration::milliseconds(millis(started) as i64);
+                return result;
+            }
+        };
+        result.backup_id = Some(backup.id.clone());
+
+        let step = Instant::now();
+        let restored = engine
+            .sandbox
+            .restore(&policy.source_id, &backup, &result.temporary_instance_id, &policy.sandbox_size)
+            .await;
+        result.restore_ms = millis(step);
+        match restored {
+            Ok(restored) => {
+                let step = Instant::now();
+                match engine.probe.validate(&restored, &backup, policy).await {
+                    Ok(checks) if checks.is_empty() => result.error = Some("No validation checks apply to this backup".into()),
+                    Ok(checks) => result.checks = checks,
+                    Err(e) => result.error = Some(format!("Validation failed: {}", e)),
+                }
+                result.validation_ms = millis(step);
+            }
+            Err(e) => result.error = Some(format!("Restore of {} failed: {}", backup.id, e)),
+        }
+
+        let validated = result.error.is_none() && !result.checks.is_empty();
+
+        let step = Instant::now();
+        if let Err(e) = engine.sandbox.teardown(&result.temporary_instance_id).await {
+            error!("Failed to tear down verification instance {}: {}", result.temporary_instance_id, e);
+            result.torn_down = false;
+            let teardown = format!("Teardown of {} failed: {}", result.temporary_instance_id, e);
+            result.error = Some(match result.error.take() {
+                Some(error) => format!("{}; {}", error, teardown),
+                None => teardown,
+            });
+        }
+        result.teardown_ms = millis(step);
+
+        if validated && result.checks.iter().all(|c| c.passed) {
+            result.outcome = VerificationOutcome::Passed;
+        }
+        result.completed_at = now + chrono::Duration::milliseconds(millis(started) as i64);
+        result
+    }
+
+    // Alerts once per source whose last passing verification is older than the policy allows.
+    // Sources that never passed count from when their policy was created.
+    pub async fn check_staleness(&self, now: DateTime<Utc>) -> DataResult<Vec<String>> {
+        let policies: Vec<VerificationPolicy> = self.policies.read().await.values().cloned().collect();
+        let mut stale = Vec::new();
+        for policy in policies {
+            let last_passed = self
+                .store
+                .list(&policy.source_id)
+                .await?
+                .into_iter()
+                .rev()
+                .find(|r| r.outcome == VerificationOutcome::Passed);
+            let since = last_passed.as_ref().map_or(policy.created_at, |r| r.completed_at);
+            if now - since <= chrono::Duration::hours(policy.max_age_hours) {
+                continue;
+            }
+            let marker = last_passed.as_ref().map(|r| r.id.clone());
+            if self.stale_alerted.lock().unwrap().get(&policy.source_id) == Some(&marker) {
+                continue;
+            }
+            warn!("Backups of {} have not passed verification since {}", policy.source_id, since);
+            if let Some(notifier) = &self.notifier {
+                if let Err(e) = notifier.verification_stale(&policy, last_passed.as_ref()).await {
+                    warn!("Failed to send stale verification alert for {}: {}", policy.source_id, e);
+                    continue;
+                }
+            }
+            self.stale_alerted.lock().unwrap().insert(policy.source_id.clone(), marker);
+            stale.push(policy.source_id);
+        }
+        stale.sort();
+        Ok(stale)
+    }
+
+    // Verifies every source that is due, one at a time so at most one temporary instance runs
+    pub async fn run_due(&self, now: DateTime<Utc>) -> DataResult<Vec<BackupVerificationResult>> {
+        let mut policies: Vec<VerificationPolicy> = self.policies.read().await.values().cloned().collect();
+        policies.sort_by(|a, b| a.source_id.cmp(&b.source_id));
+        let mut results = Vec::new();
+        for policy in policies {
+            if !self.due(&policy, now).await? {
+                continue;
+            }
+            match self.verify(&policy.source_id, now).await {
+                Ok(result) => results.push(result),
+                Err(DataError::Conflict(_) | DataError::Throttled(_)) => {}
+                Err(e) => warn!("Verification of {} could not run: {}", policy.source_id, e),
+            }
+        }
+        self.check_staleness(now).await?;
+        Ok(results)
+    }
+
+    pub fn spawn(self: Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = self.run_due(Utc::now()).await {
+                    warn!("Backup verification pass failed: {}", e);
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::testing::{instance, postgres_from_env};
+    use crate::database::{BackupManifest, BackupType};
+
+    #[derive(Default)]
+    struct Recorded {
+        backups: StdMutex<Vec<BackupJob>>,
+        restored: StdMutex<Vec<String>>,
+        torn_down: StdMutex<Vec<String>>,
+        failed: StdMutex<Vec<BackupVerificationResult>>,
+        stale: StdMutex<Vec<(String, Option<String>)>>,
+        // Restores land on this instance instead of a new one
+        target: StdMutex<Option<RestoredInstance>>,
+    }
+
+    impl Recorded {
+        fn backup(&self, id: &str, hours_ago: i64, manifest: BackupManifest) {
+            let completed = Utc::now() - chrono::Duration::hours(hours_ago);
+            self.backups.lock().unwrap().push(BackupJob {
+                id: id.to_string(),
+                instance_id: "db-orders".to_string(),
+                status: BackupStatus::Completed,
+                type_: BackupType::Automated,
+                started_at: completed,
+                completed_at: Some(completed),
+                size_bytes: 1024,
+                storage_location: format!("s3://backups/{}", id),
+                manifest: Some(manifest),
+            });
+        }
+    }
+
+    #[async_trait]
+    impl BackupCatalog for Recorded {
+        async fn list_backups(&self, _instance_id: &str) -> DataResult<Vec<BackupJob>> {
+            Ok(self.backups.lock().unwrap().clone())
+        }
+    }
+
+    #[async_trait]
+    impl RestoreSandbox for Recorded {
+        async fn restore(&self, _source_id: &str, backup: &BackupJob, instance_id: &str, _size: &str) -> DataResult<RestoredInstance> {
+            self.restored.lock().unwrap().push(instance_id.to_string());
+            if backup.id.contains("corrupt") {
+                return Err(DataError::Database("pg_restore: invalid archive header".into()));
+            }
+            Ok(self.target.lock().unwrap().clone().unwrap_or_else(|| RestoredInstance::Database(instance("localhost", 5432))))
+        }
+
+        async fn teardown(&self, instance_id: &str) -> DataResult<()> {
+            self.torn_down.lock().unwrap().push(instance_id.to_string());
+            Ok(())
+        }
+    }
+
+    // Restored tables hold whatever the manifest's "orders" count is, less one for "short" backups
+    #[async_trait]
+    impl RestoreProbe for Recorded {
+        async fn validate(&self, _restored: &RestoredInstance, backup: &BackupJob, policy: &VerificationPolicy) -> DataResult<Vec<CheckResult>> {
+            let expected = backup.manifest.as_ref().unwrap().row_counts["orders"];
+            let actual = if backup.id.contains("short") { expected - 1 } else { expected };
+            Ok(vec![CheckResult::count("row_count:orders", expected, actual, policy.count_tolerance)])
+        }
+    }
+
+    #[async_trait]
+    impl VerificationNotifier for Recorded {
+        async fn verification_failed(&self, result: &BackupVerificationResult) -> DataResult<()> {
+            self.failed.lock().unwrap().push(result.clone());
+            Ok(())
+        }
+
+        async fn verification_stale(&self, policy: &VerificationPolicy, last_passed: Option<&BackupVerificationResult>) -> DataResult<()> {
+            self.stale.lock().unwrap().push((policy.source_id.clone(), last_passed.map(|r| r.id.clone())));
+            Ok(())
+        }
+    }
+
+    fn verifier(recorded: &Arc<Recorded>, probe: Arc<dyn RestoreProbe>) -> BackupVerifier {
+        BackupVerifier::new(recorded.clone(), Arc::new(InMemoryVerificationStore::new()))
+            .with_engine(DatabaseEngine::PostgreSQL, recorded.clone(), probe)
+            .with_engine(DatabaseEngine::Redis, recorded.clone(), Arc::new(RedisProbe::new(RedisCommandAdmin::new())))
+            .with_notifier(recorded.clone())
+    }
+
+    fn manifest(rows: u64) -> BackupManifest {
+        BackupManifest { row_counts: HashMap::from([("orders".to_string(), rows)]), ..Default::default() }
+    }
+
+    #[tokio::test]
+    async fn test_throttling_failure_alerts_and_staleness() {
+        let recorded = Arc::new(Recorded::default());
+        let verifier = verifier(&recorded, recorded.clone());
+        verifier
+            .set_policy(VerificationPolicy::new("db-orders", DatabaseEngine::PostgreSQL).with_max_age_hours(48))
+            .await
+            .unwrap();
+        let now = Utc::now();
+
+        // Nothing to restore yet still counts as a failed verification
+        let missing = verifier.verify("db-orders", now).await.unwrap();
+        assert_eq!(missing.outcome, VerificationOutcome::Failed);
+        assert!(missing.error.unwrap().contains("no completed backup"));
+        assert!(recorded.restored.lock().unwrap().is_empty());
+
+        recorded.backup("bk-1", 1, manifest(120));
+        assert!(matches!(verifier.verify("db-orders", now).await, Err(DataError::Throttled(_))));
+        assert!(verifier.on_backup_completed("db-orders", now).await.unwrap().is_none());
+        let day = chrono::Duration::hours(24);
+        let passed = verifier.verify("db-orders", now + day).await.unwrap();
+        assert_eq!(passed.outcome, VerificationOutcome::Passed);
+        assert_eq!(passed.backup_id.as_deref(), Some("bk-1"));
+        assert_eq!(passed.checks[0].actual.as_deref(), Some("120"));
+        assert_eq!(*recorded.torn_down.lock().unwrap(), [passed.temporary_instance_id.clone()]);
+
+        // Corrupt archives fail at restore, short restores fail validation; both are torn down
+        recorded.backup("bk-corrupt", 0, manifest(130));
+        let corrupt = verifier.run_due(now + day * 2).await.unwrap().pop().unwrap();
+        assert_eq!(corrupt.outcome, VerificationOutcome::Failed);
+        assert!(corrupt.error.as_deref().unwrap().contains("invalid archive header"));
+        recorded.backups.lock().unwrap().clear();
+        recorded.backup("bk-short", 0, manifest(130));
+        let short = verifier.verify("db-orders", now + day * 3).await.unwrap();
+        assert_eq!((short.outcome, short.error.as_deref()), (VerificationOutcome::Failed, None));
+        assert!(!short.checks[0].passed);
+        assert_eq!(recorded.torn_down.lock().unwrap().len(), 3);
+        assert_eq!(recorded.failed.lock().unwrap().len(), 3);
+
+        // Alerted once the last pass is older than 48 hours, and only once for that pass
+        assert!(verifier.check_staleness(now + day * 2).await.unwrap().is_empty());
+        assert_eq!(verifier.check_staleness(now + day * 4).await.unwrap(), ["db-orders"]);
+        assert!(verifier.check_staleness(now + day * 5).await.unwrap().is_empty());
+        assert_eq!(*recorded.stale.lock().unwrap(), [("db-orders".to_string(), Some(passed.id.clone()))]);
+        assert_eq!(verifier.history("db-orders").await.unwrap().len(), 4);
+
+        let tolerant = VerificationPolicy::new("db-orders", DatabaseEngine::PostgreSQL).with_count_tolerance(0.05);
+        verifier.set_policy(tolerant).await.unwrap();
+        assert_eq!(verifier.verify("db-orders", now + day * 5).await.unwrap().outcome, VerificationOutcome::Passed);
+        assert!(verifier.set_policy(VerificationPolicy::new("db-x", DatabaseEngine::MySQL)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_postgres_restore_verification() {
+        let Some((source, credentials)) = postgres_from_env() else {
+            eprintln!("SIRSI_TEST_POSTGRES_URL not set, skipping");
+            return;
+        };
+        // The restore lands on the same server, so the seeded table stands in for restored data
+        let table = format!("verify_orders_{}", &uuid::Uuid::new_v4().simple().to_string()[..8]);
+        let mut conn = PgConnection::connect_with(&credentials.connect_options(&source).unwrap()).await.unwrap();
+        sqlx::raw_sql(&format!(
+            "CREATE TABLE {0} (id INT PRIMARY KEY, total INT); INSERT INTO {0} SELECT g, g * 10 FROM generate_series(1, 25) g",
+            table
+        ))
+        .execute(&mut conn)
+        .await
+        .unwrap();
+
+        let recorded = Arc::new(Recorded::default());
+        *recorded.target.lock().unwrap() = Some(RestoredInstance::Database(source.clone()));
+        let verifier = verifier(&recorded, Arc::new(PostgresProbe::new(credentials.clone())));
+        let policy = VerificationPolicy::new("db-orders", DatabaseEngine::PostgreSQL)
+            .with_min_interval_hours(1)
+            .with_smoke_query("large_orders", format!("SELECT id FROM {} WHERE total > 200", table), 5);
+        verifier.set_policy(policy).await.unwrap();
+        let manifest = |rows| BackupManifest { row_counts: HashMap::from([(table.clone(), rows)]), ..Default::default() };
+
+        recorded.backup("bk-good", 1, manifest(25));
+        let now = Utc::now();
+        let result = verifier.verify("db-orders", now).await.unwrap();
+        assert_eq!(result.outcome, VerificationOutcome::Passed, "{:?}", result);
+        let names: Vec<&str> = result.checks.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, [format!("row_count:{}", table).as_str(), "smoke:large_orders"]);
+        assert!(result.torn_down);
+
+        // A backup that recorded rows the restore doesn't have, plus a table that never came back
+        let mut corrupted = manifest(40);
+        corrupted.row_counts.insert("missing_table".into(), 3);
+        recorded.backup("bk-truncated", 0, corrupted);
+        let result = verifier.verify("db-orders", now + chrono::Duration::hours(2)).await.unwrap();
+        assert_eq!(result.outcome, VerificationOutcome::Failed);
+        let failed: Vec<&str> = result.checks.iter().filter(|c| !c.passed).map(|c| c.name.as_str()).collect();
+        assert_eq!(failed, ["row_count:missing_table", format!("row_count:{}", table).as_str()]);
+        assert_eq!(recorded.failed.lock().unwrap().len(), 1);
+
+        sqlx::raw_sql(&format!("DROP TABLE {}", table)).execute(&mut conn).await.unwrap();
+    }
+
+    // Runs when SIRSI_TEST_REDIS_URL names a disposable server, e.g. `docker run -p 6379:6379 redis:7`
+    // with SIRSI_TEST_REDIS_URL=localhost:6379; the test flushes it
+    #[tokio::test]
+    async fn test_redis_restore_verification() {
+        let Some((host, port)) = std::env::var("SIRSI_TEST_REDIS_URL").ok().and_then(|url| {
+            let (host, port) = url.rsplit_once(':')?;
+            Some((host.to_string(), port.parse::<u16>().ok()?))
+        }) else {
+            eprintln!("SIRSI_TEST_REDIS_URL not set, skipping");
+            return;
+        };
+        let node = CacheNode {
+            id: "sessions-verify-0001".into(),
+            cluster_id: "sessions-verify".into(),
+            status: cache::NodeStatus::Available,
+            address: host,
+            port,
+            availability_zone: "local".into(),
+            created_at: Utc::now(),
+            shard_id: None,
+            role: NodeRole::Primary,
+        };
+        let admin = RedisCommandAdmin::new();
+        admin.query::<()>(&node, &redis::cmd("FLUSHDB")).await.unwrap();
+        for i in 0..10 {
+            admin.query::<()>(&node, redis::cmd("SET").arg(format!("session:{}", i)).arg("live")).await.unwrap();
+        }
+
+        let recorded = Arc::new(Recorded::default());
+        *recorded.target.lock().unwrap() = Some(RestoredInstance::Cache { cluster_id: node.cluster_id.clone(), node: node.clone() });
+        let verifier = verifier(&recorded, recorded.clone());
+        verifier.set_policy(VerificationPolicy::new("sessions", DatabaseEngine::Redis).with_min_interval_hours(1)).await.unwrap();
+
+        let manifest = |keys, sample: &[&str]| BackupManifest {
+            key_count: Some(keys),
+            sample_keys: sample.iter().map(|k| k.to_string()).collect(),
+            ..Default::default()
+        };
+        recorded.backup("bk-good", 1, manifest(10, &["session:0", "session:9"]));
+        let now = Utc::now();
+        let result = verifier.verify("sessions", now).await.unwrap();
+        assert_eq!(result.outcome, VerificationOutcome::Passed, "{:?}", result);
+        assert_eq!(result.checks.len(), 2);
+
+        // The restored data lost keys the backup recorded
+        admin.query::<()>(&node, redis::cmd("DEL").arg("session:9")).await.unwrap();
+        recorded.backup("bk-evicted", 0, manifest(10, &["session:0", "session:9"]));
+        let result = verifier.verify("sessions", now + chrono::Duration::hours(2)).await.unwrap();
+        assert_eq!(result.outcome, VerificationOutcome::Failed);
+        assert!(result.checks.iter().all(|c| !c.passed));
+        assert_eq!(result.checks[1].error.as_deref(), Some("Missing keys: session:9"));
+
+        admin.query::<()>(&node, &redis::cmd("FLUSHDB")).await.unwrap();
+    }
+}
@@ -0,0 +1,137 @@
+use sirsi_common::{ErrorKind, Retryable};
+use thiserror::Error;
+use tonic::Status;
+
+#[derive(Error, Debug)]
+pub enum DataError {
+    #[error("Database error: {0}")]
+    Database(String),
+
+    #[error("Cache error: {0}")]
+    Cache(String),
+
+    #[error("Queue error: {0}")]
+    Queue(String),
+
+    #[error("Provider error: {0}")]
+    Provider(String),
+
+    #[error("Request throttled: {0}")]
+    Throttled(String),
+
+    #[error("Provider unavailable: {0}")]
+    Unavailable(String),
+
+    #[error("Authentication error: {0}")]
+    Auth(String),
+
+    #[error("Access denied: {0}")]
+    AccessDenied(String),
+
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
+    #[error("Validation error: {0}")]
+    Validation(String),
+
+    #[error("Resource not found: {0}")]
+    NotFound(String),
+
+    #[error("Configuration error: {0}")]
+    Config(String),
+
+    #[error("Internal error: {0}")]
+    Internal(String),
+}
+
+impl DataError {
+    pub fn from_kind(kind: ErrorKind, msg: impl Into<String>) -> Self {
+        let msg = msg.into();
+        match kind {
+            ErrorKind::Throttled => DataError::Throttled(msg),
+            ErrorKind::ProviderOutage => DataError::Unavailable(msg),
+            ErrorKind::AuthFailure => DataError::Auth(msg),
+            ErrorKind::NotFound => DataError::NotFound(msg),
+            ErrorKind::Conflict => DataError::Conflict(msg),
+            ErrorKind::InvalidInput => DataError::Validation(msg),
+            ErrorKind::Internal => DataError::Provider(msg),
+        }
+    }
+
+    pub fn from_aws_code(code: Option<&str>, msg: impl Into<String>) -> Self {
+        let kind = code.map(ErrorKind::from_aws_code).unwrap_or(ErrorKind::Internal);
+        Self::from_kind(kind, msg)
+    }
+
+    pub fn from_gcp_status(http_code: Option<u16>, status: Option<&str>, msg: impl Into<String>) -> Self {
+        Self::from_kind(ErrorKind::from_gcp_status(http_code, status), msg)
+    }
+
+    pub fn from_http_status(status: u16, msg: impl Into<String>) -> Self {
+        Self::from_kind(ErrorKind::from_http_status(status), msg)
+    }
+}
+
+impl Retryable for DataError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            DataError::Database(_) => ErrorKind::Internal,
+            DataError::Cache(_) => ErrorKind::Internal,
+            DataError::Queue(_) => ErrorKind::Internal,
+            DataError::Provider(_) => ErrorKind::Internal,
+            DataError::Throttled(_) => ErrorKind::Throttled,
+            DataError::Unavailable(_) => ErrorKind::ProviderOutage,
+            DataError::Auth(_) => ErrorKind::AuthFailure,
+            DataError::AccessDenied(_) => ErrorKind::AuthFailure,
+            DataError::Conflict(_) => ErrorKind::Conflict,
+            DataError::Validation(_) => ErrorKind::InvalidInput,
+            DataError::NotFound(_) => ErrorKind::NotFound,
+            DataError::Config(_) => ErrorKind::InvalidInput,
+            DataError::Internal(_) => ErrorKind::Internal,
+        }
+    }
+}
+
+impl From<DataError> for Status {
+    fn from(error: DataError) -> Self {
+        match error {
+            DataError::Database(msg) => Status::internal(msg),
+            DataError::Cache(msg) => Status::internal(msg),
+            DataError::Queue(msg) => Status::internal(msg),
+            DataError::Provider(msg) => Status::internal(msg),
+            DataError::Throttled(msg) => Status::resource_exhausted(msg),
+            DataError::Unavailable(msg) => Status::unavailable(msg),
+            DataError::Auth(msg) => Status::unauthenticated(msg),
+            DataError::AccessDenied(msg) => Status::permission_denied(msg),
+            DataError::Conflict(msg) => Status::aborted(msg),
+            DataError::Validation(msg) => Status::invalid_argument(msg),
+            DataError::NotFound(msg) => Status::not_found(msg),
+            DataError::Config(msg) => Status::failed_precondition(msg),
+            DataError::Internal(msg) => Status::internal(msg),
+        }
+    }
+}
+
+pub type DataResult<T> = Result<T, DataError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_provider_errors_classify() {
+        let err = DataError::from_aws_code(Some("ProvisionedThroughputExceededException"), "Failed to write item");
+        assert!(matches!(err, DataError::Throttled(_)));
+        assert!(err.is_retryable());
+
+        let err = DataError::from_aws_code(Some("DBInstanceNotFound"), "Failed to describe instance");
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+
+        let err = DataError::from_gcp_status(Some(429), Some("RESOURCE_EXHAUSTED"), "Failed to publish");
+        assert_eq!(err.kind(), ErrorKind::Throttled);
+
+        let err = DataError::from_http_status(409, "Queue already exists");
+        assert_eq!(err.kind(), ErrorKind::Conflict);
+        assert!(!err.is_retryable());
+    }
+}
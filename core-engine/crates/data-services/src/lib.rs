@@ -0,0 +1,6 @@
+pub mod error;
+pub mod cache;
+pub mod database;
+pub mod queue;
+
+pub use error::{DataError, DataResult};
@@ -0,0 +1,473 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use sirsi_common::Retryable;
+use sirsi_key_vault::crypto::CryptoService;
+use sirsi_key_vault::KeyVaultError;
+use sirsi_observability::monitoring::{MetricDataPoint, MetricValue, MetricsManager};
+use tokio::sync::Mutex;
+use tracing::{debug, warn};
+
+use crate::error::{DataError, DataResult};
+use super::lag::QUEUE_ID_DIMENSION;
+use super::{Message, MessageOperations, QueueManager};
+
+// Attributes carried by an encrypted message; `data` holds nonce || AES-256-GCM(payload)
+pub const ENCRYPTION_KEY_ATTRIBUTE: &str = "x-sirsi-enc-key";
+pub const ENCRYPTION_KEY_VERSION_ATTRIBUTE: &str = "x-sirsi-enc-key-version";
+pub const WRAPPED_KEY_ATTRIBUTE: &str = "x-sirsi-enc-wrapped-key";
+pub const ENCRYPTION_ALGORITHM_ATTRIBUTE: &str = "x-sirsi-enc-alg";
+pub const AES_256_GCM_ALGORITHM: &str = "AES-256-GCM";
+
+pub const ENCRYPTION_METRICS_NAMESPACE: &str = "Sirsi/Queues";
+pub const MESSAGES_SENT_METRIC: &str = "MessagesSent";
+pub const BYTES_SENT_METRIC: &str = "BytesSent";
+pub const PAYLOAD_MODE_DIMENSION: &str = "PayloadMode";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EncryptionStats {
+    pub encrypted_messages: u64,
+    pub encrypted_bytes: u64,
+    pub plaintext_messages: u64,
+    pub plaintext_bytes: u64,
+    pub decrypted_messages: u64,
+    pub access_denied: u64,
+}
+
+#[derive(Default)]
+struct QueueCounters {
+    encrypted_messages: AtomicU64,
+    encrypted_bytes: AtomicU64,
+    plaintext_messages: AtomicU64,
+    plaintext_bytes: AtomicU64,
+    decrypted_messages: AtomicU64,
+    access_denied: AtomicU64,
+}
+
+impl QueueCounters {
+    fn snapshot(&self) -> EncryptionStats {
+        EncryptionStats {
+            encrypted_messages: self.encrypted_messages.load(Ordering::Relaxed),
+            encrypted_bytes: self.encrypted_bytes.load(Ordering::Relaxed),
+            plaintext_messages: self.plaintext_messages.load(Ordering::Relaxed),
+            plaintext_bytes: self.plaintext_bytes.load(Ordering::Relaxed),
+            decrypted_messages: self.decrypted_messages.load(Ordering::Relaxed),
+            access_denied: self.access_denied.load(Ordering::Relaxed),
+        }
+    }
+}
+
+fn vault_error(queue_id: &str, e: KeyVaultError) -> DataError {
+    match e {
+        KeyVaultError::Permission(msg) => {
+            DataError::AccessDenied(format!("Cannot decrypt messages on queue {}: {}", queue_id, msg))
+        }
+        KeyVaultError::NotFound(msg) => DataError::NotFound(msg),
+        e => DataError::Provider(format!("Key vault operation for queue {} failed: {}", queue_id, e)),
+    }
+}
+
+fn cipher(key: &[u8]) -> DataResult<LessSafeKey> {
+    let unbound = UnboundKey::new(&AES_256_GCM, key).map_err(|_| DataError::Internal("Invalid data key".into()))?;
+    Ok(LessSafeKey::new(unbound))
+}
+
+// Transparent envelope encryption over another backend for queues whose config opts in. The
+// backend only ever stores ciphertext plus the key-vault wrapped data key; consumers need
+// decrypt access on the queue's key to read payloads.
+pub struct EncryptedMessageOperations {
+    inner: Arc<dyn MessageOperations>,
+    queues: Arc<dyn QueueManager>,
+    crypto: Arc<CryptoService>,
+    principal: String,
+    rng: SystemRandom,
+    counters: std::sync::RwLock<HashMap<String, Arc<QueueCounters>>>,
+    published: Mutex<HashMap<String, EncryptionStats>>,
+}
+
+impl EncryptedMessageOperations {
+    pub fn new(
+        inner: Arc<dyn MessageOperations>,
+        queues: Arc<dyn QueueManager>,
+        crypto: Arc<CryptoService>,
+        principal: impl Into<String>,
+    ) -> Self {
+        Self {
+            inner,
+            queues,
+            crypto,
+            principal: principal.into(),
+            rng: SystemRandom::new(),
+            counters: std::sync::RwLock::new(HashMap::new()),
+            published: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn counters(&self, queue_id: &str) -> Arc<QueueCounters> {
+        if let Some(counters) = self.counters.read().expect("counters lock poisoned").get(queue_id) {
+            return counters.clone();
+        }
+        self.counters
+            .write()
+            .expect("counters lock poisoned")
+            .entry(queue_id.to_string())
+            .or_default()
+            .clone()
+    }
+
+    pub fn stats(&self, queue_id: &str) -> EncryptionStats {
+        self.counters
+            .read()
+            .expect("counters lock poisoned")
+            .get(queue_id)
+            .map(|c| c.snapshot())
+            .unwrap_or_default()
+    }
+
+    async fn seal(&self, queue_id: &str, mut message: Message) -> DataResult<Message> {
+        let counters = self.counters(queue_id);
+        let queue = self.queues.get_queue(queue_id).await?;
+        let Some(encryption) = queue.config.encryption else {
+            counters.plaintext_messages.fetch_add(1, Ordering::Relaxed);
+            counters.plaintext_bytes.fetch_add(message.data.len() as u64, Ordering::Relaxed);
+            return Ok(message);
+        };
+        if message.attributes.contains_key(WRAPPED_KEY_ATTRIBUTE) {
+            return Err(DataError::Validation(format!("Message {} is already encrypted", message.id)));
+        }
+
+        let data_key = self
+            .crypto
+            .generate_data_key(&self.principal, &encryption.key_id)
+            .await
+            .map_err(|e| vault_error(queue_id, e))?;
+        // The id is part of the AAD so ciphertexts cannot be swapped between messages
+        if message.id.is_empty() {
+            message.id = uuid::Uuid::new_v4().to_string();
+        }
+        let mut nonce = [0u8; NONCE_LEN];
+        self.rng
+            .fill(&mut nonce)
+            .map_err(|_| DataError::Internal("System random source failed".into()))?;
+        let plaintext_len = message.data.len() as u64;
+        let mut sealed = std::mem::take(&mut message.data);
+        cipher(&data_key.plaintext)?
+            .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::from(message.id.as_bytes()), &mut sealed)
+            .map_err(|_| DataError::Internal(format!("Failed to encrypt message {}", message.id)))?;
+        message.data = nonce.to_vec();
+        message.data.extend_from_slice(&sealed);
+
+        message
+            .attributes
+            .insert(ENCRYPTION_KEY_ATTRIBUTE.to_string(), data_key.key_name.clone());
+        message
+            .attributes
+            .insert(ENCRYPTION_KEY_VERSION_ATTRIBUTE.to_string(), data_key.key_version.to_string());
+        message
+            .attributes
+            .insert(WRAPPED_KEY_ATTRIBUTE.to_string(), hex::encode(&data_key.wrapped));
+        message
+            .attributes
+            .insert(ENCRYPTION_ALGORITHM_ATTRIBUTE.to_string(), AES_256_GCM_ALGORITHM.to_string());
+        counters.encrypted_messages.fetch_add(1, Ordering::Relaxed);
+        counters.encrypted_bytes.fetch_add(plaintext_len, Ordering::Relaxed);
+        Ok(message)
+    }
+
+    // Messages without the encryption attributes pass through, so a queue can opt in while
+    // plaintext messages are still in flight
+    async fn open(&self, queue_id: &str, mut message: Message) -> DataResult<Message> {
+        let Some(wrapped) = message.attributes.remove(WRAPPED_KEY_ATTRIBUTE) else {
+            return Ok(message);
+        };
+        let malformed = |what: &str| DataError::Validation(format!("Encrypted message {} has {}", message.id, what));
+        let key_id = message
+            .attributes
+            .remove(ENCRYPTION_KEY_ATTRIBUTE)
+            .ok_or_else(|| malformed("no key id"))?;
+        let version = message
+            .attributes
+            .remove(ENCRYPTION_KEY_VERSION_ATTRIBUTE)
+            .and_then(|v| v.parse::<u32>().ok())
+            .ok_or_else(|| malformed("no key version"))?;
+        match message.attributes.remove(ENCRYPTION_ALGORITHM_ATTRIBUTE).as_deref() {
+            Some(AES_256_GCM_ALGORITHM) => {}
+            _ => return Err(malformed("an unsupported algorithm")),
+        }
+        let wrapped = hex::decode(wrapped).map_err(|_| malformed("a malformed wrapped key"))?;
+        if message.data.len() < NONCE_LEN {
+            return Err(malformed("a truncated payload"));
+        }
+
+        let counters = self.counters(queue_id);
+        let data_key = match self.crypto.decrypt_data_key(&self.principal, &key_id, version, &wrapped).await {
+            Ok(key) => key,
+            Err(e) => {
+                let e = vault_error(queue_id, e);
+                if matches!(e, DataError::AccessDenied(_)) {
+                    counters.access_denied.fetch_add(1, Ordering::Relaxed);
+                }
+                return Err(e);
+            }
+        };
+        let nonce: [u8; NONCE_LEN] = message.data[..NONCE_LEN].try_into().expect("slice is nonce length");
+        let mut sealed = message.data.split_off(NONCE_LEN);
+        let plaintext_len = cipher(&data_key)?
+            .open_in_place(Nonce::assume_unique_for_key(nonce), Aad::from(message.id.as_bytes()), &mut sealed)
+            .map_err(|_| DataError::Validation(format!("Encrypted message {} failed authentication", message.id)))?
+            .len();
+        sealed.truncate(plaintext_len);
+        message.data = sealed;
+        counters.decrypted_messages.fetch_add(1, Ordering::Relaxed);
+        Ok(message)
+    }
+
+    // On failure the received messages are made visible again; they were never handed out
+    async fn open_received(&self, queue_id: &str, messages: Vec<Message>) -> DataResult<Vec<Message>> {
+        let ids: Vec<String> = messages.iter().map(|m| m.id.clone()).collect();
+        let mut opened = Vec::with_capacity(messages.len());
+        for message in messages {
+            match self.open(queue_id, message).await {
+                Ok(message) => opened.push(message),
+                Err(e) => {
+                    warn!("Could not decrypt messages received on {} as {}: {}", queue_id, self.principal, e);
+                    for id in &ids {
+                        if let Err(e) = self.inner.change_visibility(queue_id, id, chrono::Duration::zero()).await {
+                            debug!("Could not release message {} on {}: {}", id, queue_id, e);
+                        }
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        Ok(opened)
+    }
+
+    async fn open_all(&self, queue_id: &str, messages: Vec<Message>) -> DataResult<Vec<Message>> {
+        let mut opened = Vec::with_capacity(messages.len());
+        for message in messages {
+            opened.push(self.open(queue_id, message).await?);
+        }
+        Ok(opened)
+    }
+
+    // Publishes per-queue message and byte counts since the last call, split by payload mode
+    pub async fn publish_metrics(&self, metrics: &dyn MetricsManager) -> DataResult<()> {
+        let current: Vec<(String, EncryptionStats)> = self
+            .counters
+            .read()
+            .expect("counters lock poisoned")
+            .iter()
+            .map(|(queue, counters)| (queue.clone(), counters.snapshot()))
+            .collect();
+        let mut published = self.published.lock().await;
+        let now = Utc::now();
+        let mut points = Vec::new();
+        for (queue_id, stats) in &current {
+            let last = published.get(queue_id).copied().unwrap_or_default();
+            for (mode, messages, bytes) in [
+                ("encrypted", stats.encrypted_messages - last.encrypted_messages, stats.encrypted_bytes - last.encrypted_bytes),
+                ("plaintext", stats.plaintext_messages - last.plaintext_messages, stats.plaintext_bytes - last.plaintext_bytes),
+            ] {
+                let dimensions = HashMap::from([
+                    (QUEUE_ID_DIMENSION.to_string(), queue_id.clone()),
+                    (PAYLOAD_MODE_DIMENSION.to_string(), mode.to_string()),
+                ]);
+                for (name, value) in [(MESSAGES_SENT_METRIC, messages), (BYTES_SENT_METRIC, bytes)] {
+                    points.push(MetricDataPoint {
+                        name: name.to_string(),
+                        namespace: ENCRYPTION_METRICS_NAMESPACE.to_string(),
+                        dimensions: dimensions.clone(),
+                        timestamp: now,
+                        value: MetricValue::Single(value as f64),
+                        exemplars: Vec::new(),
+                    });
+                }
+            }
+        }
+        if !points.is_empty() {
+            metrics
+                .put_metric_data(points)
+                .await
+                .map_err(|e| DataError::from_kind(e.kind(), e.to_string()))?;
+        }
+        published.extend(current);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MessageOperations for EncryptedMessageOperations {
+    async fn send_message(&self, queue_id: &str, message: Message) -> DataResult<String> {
+        let sealed = self.seal(queue_id, message).await?;
+        self.inner.send_message(queue_id, sealed).await
+    }
+
+    async fn send_batch(&self, queue_id: &str, messages: Vec<Message>) -> DataResult<Vec<String>> {
+        let mut sealed = Vec::with_capacity(messages.len());
+        for message in messages {
+            sealed.push(self.seal(queue_id, message).await?);
+        }
+        self.inner.send_batch(queue_id, sealed).await
+    }
+
+    async fn receive_messages(&self, queue_id: &str, max_messages: i32, wait_time_seconds: i32) -> DataResult<Vec<Message>> {
+        let messages = self.inner.receive_messages(queue_id, max_messages, wait_time_seconds).await?;
+        self.open_received(queue_id, messages).await
+    }
+
+    async fn delete_message(&self, queue_id: &str, message_id: &str) -> DataResult<()> {
+        self.inner.delete_message(queue_id, message_id).await
+    }
+
+    async fn peek_messages(&self, queue_id: &str, count: i32) -> DataResult<Vec<Message>> {
+        let messages = self.inner.peek_messages(queue_id, count).await?;
+        self.open_all(queue_id, messages).await
+    }
+
+    async fn change_visibility(&self, queue_id: &str, message_id: &str, delay: chrono::Duration) -> DataResult<()> {
+        self.inner.change_visibility(queue_id, message_id, delay).await
+    }
+
+    async fn send_scheduled(&self, queue_id: &str, message: Message, deliver_at: DateTime<Utc>) -> DataResult<String> {
+        let sealed = self.seal(queue_id, message).await?;
+        self.inner.send_scheduled(queue_id, sealed, deliver_at).await
+    }
+
+    async fn list_scheduled(&self, queue_id: &str) -> DataResult<Vec<Message>> {
+        let messages = self.inner.list_scheduled(queue_id).await?;
+        self.open_all(queue_id, messages).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::queue::{
+        DeliveryMode, DurabilityLevel, InMemoryQueueBackend, Queue, QueueConfig, QueueEncryption, QueueEngine,
+        QueueStatus,
+    };
+    use sirsi_key_vault::secret::{
+        AccessPolicy, AccessPolicyManager, InMemoryAccessPolicyManager, InMemoryAuditLogger, SecretAction,
+        SecretPermission,
+    };
+
+    fn queue(id: &str, encryption: Option<QueueEncryption>) -> Queue {
+        Queue {
+            id: id.into(),
+            name: id.into(),
+            engine: QueueEngine::RabbitMQ,
+            config: QueueConfig {
+                max_size_gb: 1,
+                message_retention_days: 1,
+                durability: DurabilityLevel::Disk,
+                delivery_mode: DeliveryMode::AtLeastOnce,
+                max_message_size_kb: 256,
+                supports_partitioning: false,
+                partition_count: None,
+                replication_factor: 1,
+                dead_letter_queue: None,
+                encryption,
+            },
+            status: QueueStatus::Active,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            tags: HashMap::new(),
+        }
+    }
+
+    fn message(data: &[u8]) -> Message {
+        Message {
+            id: String::new(),
+            queue_id: String::new(),
+            data: data.to_vec(),
+            attributes: HashMap::from([("tenant".to_string(), "acme".to_string())]),
+            publish_time: Utc::now(),
+            delivery_count: 0,
+            scheduled_for: None,
+            correlation_id: None,
+            reply_to: None,
+        }
+    }
+
+    // svc-orders may encrypt and decrypt; svc-reporting may only encrypt
+    async fn setup() -> (Arc<InMemoryQueueBackend>, Arc<CryptoService>) {
+        let policies = InMemoryAccessPolicyManager::new();
+        for (principal, actions) in [
+            ("admin", vec![SecretAction::Write, SecretAction::Rotate]),
+            ("svc-orders", vec![SecretAction::Encrypt, SecretAction::Decrypt]),
+            ("svc-reporting", vec![SecretAction::Encrypt]),
+        ] {
+            policies
+                .create_policy(AccessPolicy {
+                    id: principal.into(),
+                    name: principal.into(),
+                    description: None,
+                    principals: vec![principal.into()],
+                    permissions: vec![SecretPermission {
+                        actions,
+                        secret_patterns: vec!["crypto/*".into()],
+                    }],
+                    conditions: None,
+                })
+                .await
+                .unwrap();
+        }
+        let crypto = Arc::new(CryptoService::new(Arc::new(policies), Arc::new(InMemoryAuditLogger::new())));
+        crypto.create_key("admin", "orders-key").await.unwrap();
+
+        let backend = Arc::new(InMemoryQueueBackend::new());
+        let encryption = QueueEncryption { key_id: "orders-key".into() };
+        backend.create_queue(queue("orders", Some(encryption))).await.unwrap();
+        backend.create_queue(queue("audit", None)).await.unwrap();
+        (backend, crypto)
+    }
+
+    #[tokio::test]
+    async fn test_rotation_mid_flight_keeps_messages_readable() {
+        let (backend, crypto) = setup().await;
+        let queues = EncryptedMessageOperations::new(backend.clone(), backend.clone(), crypto.clone(), "svc-orders");
+
+        queues.send_message("orders", message(b"before rotation")).await.unwrap();
+        crypto.rotate_key("admin", "orders-key").await.unwrap();
+        queues.send_message("orders", message(b"after rotation")).await.unwrap();
+        queues.send_message("audit", message(b"plain")).await.unwrap();
+
+        let stored = backend.peek_messages("orders", 10).await.unwrap();
+        let versions: Vec<&str> = stored.iter().map(|m| m.attributes[ENCRYPTION_KEY_VERSION_ATTRIBUTE].as_str()).collect();
+        assert_eq!(versions, ["1", "2"]);
+        assert!(stored.iter().all(|m| !m.data.windows(8).any(|w| w == b"rotation")));
+
+        let received = queues.receive_messages("orders", 10, 0).await.unwrap();
+        let payloads: Vec<&[u8]> = received.iter().map(|m| m.data.as_slice()).collect();
+        assert_eq!(payloads, [b"before rotation".as_slice(), b"after rotation".as_slice()]);
+        assert!(received.iter().all(|m| m.attributes.len() == 1 && m.attributes["tenant"] == "acme"));
+        assert_eq!(backend.peek_messages("audit", 1).await.unwrap()[0].data, b"plain");
+
+        let orders = queues.stats("orders");
+        assert_eq!((orders.encrypted_messages, orders.encrypted_bytes, orders.decrypted_messages), (2, 29, 2));
+        assert_eq!(queues.stats("audit").plaintext_messages, 1);
+    }
+
+    #[tokio::test]
+    async fn test_unauthorized_consumer_gets_access_denied() {
+        let (backend, crypto) = setup().await;
+        let producer = EncryptedMessageOperations::new(backend.clone(), backend.clone(), crypto.clone(), "svc-orders");
+        let reporting = EncryptedMessageOperations::new(backend.clone(), backend.clone(), crypto, "svc-reporting");
+
+        producer.send_message("orders", message(b"card on file")).await.unwrap();
+        let denied = reporting.receive_messages("orders", 10, 0).await;
+        assert!(matches!(denied, Err(DataError::AccessDenied(_))));
+        assert_eq!(reporting.stats("orders").access_denied, 1);
+
+        // The denied receive did not consume the message
+        let received = producer.receive_messages("orders", 10, 0).await.unwrap();
+        assert_eq!(received[0].data, b"card on file");
+    }
+}
@@ -0,0 +1,384 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sirsi_common::Retryable;
+use sirsi_observability::monitoring::{
+    AggregationType, MetricDataPoint, MetricDefinition, MetricType, MetricUnit, MetricValue, MetricsManager,
+};
+use sirsi_observability::ObservabilityError;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+use crate::error::{DataError, DataResult};
+use super::{Queue, QueueEngine, QueueManager, QueueMetrics, Subscription, SubscriptionManager};
+
+pub const DEFAULT_LAG_NAMESPACE: &str = "Sirsi/Queues";
+pub const CONSUMER_LAG_METRIC: &str = "ConsumerLag";
+pub const OLDEST_MESSAGE_AGE_METRIC: &str = "OldestMessageAge";
+
+// Dimension keys on the published lag series. `Scope` tells queue-wide, subscription and
+// consumer group series apart, so a query on `QueueId` alone can still select one of them.
+pub const QUEUE_ID_DIMENSION: &str = "QueueId";
+pub const QUEUE_NAME_DIMENSION: &str = "QueueName";
+pub const SUBSCRIPTION_DIMENSION: &str = "Subscription";
+pub const CONSUMER_GROUP_DIMENSION: &str = "ConsumerGroup";
+pub const SCOPE_DIMENSION: &str = "Scope";
+
+fn observability_error(error: ObservabilityError) -> DataError {
+    DataError::from_kind(error.kind(), error.to_string())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConsumerLag {
+    pub queue_id: String,
+    pub queue_name: String,
+    pub subscription_id: Option<String>,
+    pub consumer_group: Option<String>,
+    pub messages_behind: i64,
+    pub oldest_message_age_seconds: i64,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl ConsumerLag {
+    pub fn scope(&self) -> &'static str {
+        match (&self.subscription_id, &self.consumer_group) {
+            (Some(_), _) => "subscription",
+            (None, Some(_)) => "consumer_group",
+            (None, None) => "queue",
+        }
+    }
+
+    pub fn dimensions(&self) -> HashMap<String, String> {
+        let mut dimensions = HashMap::from([
+            (QUEUE_ID_DIMENSION.to_string(), self.queue_id.clone()),
+            (QUEUE_NAME_DIMENSION.to_string(), self.queue_name.clone()),
+            (SCOPE_DIMENSION.to_string(), self.scope().to_string()),
+        ]);
+        if let Some(subscription) = &self.subscription_id {
+            dimensions.insert(SUBSCRIPTION_DIMENSION.to_string(), subscription.clone());
+        }
+        if let Some(group) = &self.consumer_group {
+            dimensions.insert(CONSUMER_GROUP_DIMENSION.to_string(), group.clone());
+        }
+        dimensions
+    }
+}
+
+// Lag for the queue as a whole, for each subscription, and for Kafka consumer groups no
+// subscription accounts for. A Kafka subscription reads its own consumer group, named after the
+// subscription's name or id; on other engines every subscription drains the shared backlog.
+pub fn consumer_lag(queue: &Queue, metrics: &QueueMetrics, subscriptions: &[Subscription]) -> Vec<ConsumerLag> {
+    let row = |subscription: Option<&Subscription>, group: Option<&str>, behind: i64, age: i64| ConsumerLag {
+        queue_id: queue.id.clone(),
+        queue_name: queue.name.clone(),
+        subscription_id: subscription.map(|s| s.id.clone()),
+        consumer_group: group.map(str::to_string),
+        messages_behind: behind,
+        oldest_message_age_seconds: age,
+        timestamp: metrics.timestamp,
+    };
+    let kafka = matches!(queue.engine, QueueEngine::Kafka);
+    let mut lag = vec![row(None, None, metrics.messages_available, metrics.oldest_message_age_seconds)];
+
+    let mut claimed = HashSet::new();
+    for subscription in subscriptions {
+        let group = metrics
+            .consumer_groups
+            .iter()
+            .find(|g| g.group == subscription.name || g.group == subscription.id);
+        match group {
+            Some(group) => {
+                claimed.insert(group.group.as_str());
+                let age = group.oldest_unconsumed_age_seconds.unwrap_or(metrics.oldest_message_age_seconds);
+                lag.push(row(Some(subscription), Some(&group.group), group.total_lag(), age));
+            }
+            // Without a committed offset the topic size says nothing about this consumer
+            None if kafka => debug!("Subscription {} has no consumer group on {}", subscription.id, queue.id),
+            None => lag.push(row(
+                Some(subscription),
+                None,
+                metrics.messages_available,
+                metrics.oldest_message_age_seconds,
+            )),
+        }
+    }
+    for group in metrics.consumer_groups.iter().filter(|g| !claimed.contains(g.group.as_str())) {
+        let age = group.oldest_unconsumed_age_seconds.unwrap_or(metrics.oldest_message_age_seconds);
+        lag.push(row(None, Some(&group.group), group.total_lag(), age));
+    }
+    lag
+}
+
+// Publishes consumer lag for every queue as observability metrics, which autoscaling policies
+// then read back by namespace and dimensions
+pub struct LagPublisher {
+    queues: Arc<dyn QueueManager>,
+    subscriptions: Option<Arc<dyn SubscriptionManager>>,
+    metrics: Arc<dyn MetricsManager>,
+    namespace: String,
+}
+
+impl LagPublisher {
+    pub fn new(queues: Arc<dyn QueueManager>, metrics: Arc<dyn MetricsManager>) -> Self {
+        Self {
+            queues,
+            subscriptions: None,
+            metrics,
+            namespace: DEFAULT_LAG_NAMESPACE.to_string(),
+        }
+    }
+
+    pub fn with_subscriptions(mut self, subscriptions: Arc<dyn SubscriptionManager>) -> Self {
+        self.subscriptions = Some(subscriptions);
+        self
+    }
+
+    pub fn with_namespace(mut self, namespace: impl Into<String>) -> Self {
+        self.namespace = namespace.into();
+        self
+    }
+
+    pub async fn register_metrics(&self) -> DataResult<()> {
+        let dimensions = [
+            QUEUE_ID_DIMENSION,
+            QUEUE_NAME_DIMENSION,
+            SUBSCRIPTION_DIMENSION,
+            CONSUMER_GROUP_DIMENSION,
+            SCOPE_DIMENSION,
+        ];
+        for (name, unit) in [(CONSUMER_LAG_METRIC, MetricUnit::Count), (OLDEST_MESSAGE_AGE_METRIC, MetricUnit::Seconds)] {
+            self.metrics
+                .register_metric(MetricDefinition {
+                    name: name.to_string(),
+                    namespace: self.namespace.clone(),
+                    metric_type: MetricType::Gauge,
+                    unit,
+                    dimensions: dimensions.iter().map(|d| d.to_string()).collect(),
+                    aggregations: vec![AggregationType::Average, AggregationType::Maximum],
+                    retention_days: 15,
+                })
+                .await
+                .map_err(observability_error)?;
+        }
+        Ok(())
+    }
+
+    // A queue whose metrics cannot be read is skipped so one broken queue does not hide the rest
+    pub async fn collect(&self) -> DataResult<Vec<ConsumerLag>> {
+        let mut lag = Vec::new();
+        for queue in self.queues.list_queues().await? {
+            let latest = match self.queues.get_metrics(&queue.id, chrono::Duration::minutes(5)).await {
+                Ok(metrics) => metrics.into_iter().max_by_key(|m| m.timestamp),
+                Err(e) => {
+                    warn!("Failed to read metrics for queue {}: {}", queue.id, e);
+                    continue;
+                }
+            };
+            let Some(latest) = latest else { continue };
+            let subscriptions = match &self.subscriptions {
+                Some(manager) => manager.list_subscriptions(&queue.id).await.unwrap_or_else(|e| {
+                    warn!("Failed to list subscriptions for queue {}: {}", queue.id, e);
+                    Vec::new()
+                }),
+                None => Vec::new(),
+            };
+            lag.extend(consumer_lag(&queue, &latest, &subscriptions));
+        }
+        Ok(lag)
+    }
+
+    pub async fn publish(&self) -> DataResult<Vec<ConsumerLag>> {
+        let lag = self.collect().await?;
+        let points = lag
+            .iter()
+            .flat_map(|row| {
+                let dimensions = row.dimensions();
+                [
+                    (CONSUMER_LAG_METRIC, row.messages_behind),
+                    (OLDEST_MESSAGE_AGE_METRIC, row.oldest_message_age_seconds),
+                ]
+                .map(|(name, value)| MetricDataPoint {
+                    name: name.to_string(),
+                    namespace: self.namespace.clone(),
+                    dimensions: dimensions.clone(),
+                    timestamp: row.timestamp,
+                    value: MetricValue::Single(value as f64),
+                    exemplars: Vec::new(),
+                })
+            })
+            .collect::<Vec<_>>();
+        if !points.is_empty() {
+            self.metrics.put_metric_data(points).await.map_err(observability_error)?;
+        }
+        Ok(lag)
+    }
+
+    pub fn spawn_publisher(self: Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = self.publish().await {
+                    warn!("Consumer lag publishing failed: {}", e);
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::queue::{
+        ConsumerGroupLag, DeliveryMode, DurabilityLevel, InMemoryQueueBackend, MessageOperations, Message, PartitionLag,
+        QueueConfig, QueueStatus, RetryPolicy, SubscriptionEndpoint, SubscriptionStatus,
+    };
+    use sirsi_observability::monitoring::MetricQuery;
+    use sirsi_observability::ObservabilityResult;
+    use std::sync::Mutex;
+
+    fn queue(id: &str, engine: QueueEngine) -> Queue {
+        Queue {
+            id: id.into(),
+            name: format!("{}-name", id),
+            engine,
+            config: QueueConfig {
+                max_size_gb: 1,
+                message_retention_days: 1,
+                durability: DurabilityLevel::Disk,
+                delivery_mode: DeliveryMode::AtLeastOnce,
+                max_message_size_kb: 256,
+                supports_partitioning: true,
+                partition_count: Some(2),
+                replication_factor: 1,
+                dead_letter_queue: None,
+                encryption: None,
+            },
+            status: QueueStatus::Active,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            tags: HashMap::new(),
+        }
+    }
+
+    fn subscription(id: &str, name: &str) -> Subscription {
+        Subscription {
+            id: id.into(),
+            queue_id: "orders".into(),
+            name: name.into(),
+            filter_expression: None,
+            endpoint: SubscriptionEndpoint::HTTP { url: "http://worker".into(), headers: HashMap::new() },
+            retry_policy: RetryPolicy {
+                max_retries: 3,
+                initial_retry_delay_seconds: 1,
+                max_retry_delay_seconds: 10,
+                retry_multiplier: 2.0,
+            },
+            dead_letter_queue: None,
+            status: SubscriptionStatus::Active,
+        }
+    }
+
+    #[test]
+    fn test_kafka_lag_follows_consumer_groups() {
+        let group = |name: &str, offsets: &[(i64, i64)]| ConsumerGroupLag {
+            group: name.into(),
+            partitions: offsets
+                .iter()
+                .enumerate()
+                .map(|(i, (committed, end))| PartitionLag {
+                    partition: i as i32,
+                    committed_offset: *committed,
+                    log_end_offset: *end,
+                })
+                .collect(),
+            oldest_unconsumed_age_seconds: Some(30),
+        };
+        let metrics = QueueMetrics {
+            queue_id: "orders".into(),
+            timestamp: Utc::now(),
+            messages_available: 5_000,
+            messages_in_flight: 0,
+            messages_delayed: 0,
+            oldest_message_age_seconds: 600,
+            size_bytes: 0,
+            throughput_per_second: 0.0,
+            // A committed offset past the reported end (stale metadata) counts as caught up
+            consumer_groups: vec![group("billing", &[(90, 100), (40, 80)]), group("audit", &[(100, 100), (90, 80)])],
+        };
+        let subscriptions = [subscription("sub-billing", "billing"), subscription("sub-orphan", "search")];
+        let lag = consumer_lag(&queue("orders", QueueEngine::Kafka), &metrics, &subscriptions);
+
+        assert_eq!(lag.len(), 3);
+        assert_eq!((lag[0].scope(), lag[0].messages_behind), ("queue", 5_000));
+        assert_eq!(lag[1].subscription_id.as_deref(), Some("sub-billing"));
+        assert_eq!((lag[1].messages_behind, lag[1].oldest_message_age_seconds), (50, 30));
+        assert_eq!(lag[2].consumer_group.as_deref(), Some("audit"));
+        assert_eq!(lag[2].messages_behind, 0);
+        assert_eq!(lag[1].dimensions()[SCOPE_DIMENSION], "subscription");
+    }
+
+    #[derive(Default)]
+    struct RecordingMetrics {
+        points: Mutex<Vec<MetricDataPoint>>,
+    }
+
+    #[async_trait::async_trait]
+    impl MetricsManager for RecordingMetrics {
+        async fn register_metric(&self, _definition: MetricDefinition) -> ObservabilityResult<()> {
+            Ok(())
+        }
+
+        async fn put_metric_data(&self, data_points: Vec<MetricDataPoint>) -> ObservabilityResult<()> {
+            self.points.lock().unwrap().extend(data_points);
+            Ok(())
+        }
+
+        async fn get_metric_data(&self, _query: MetricQuery) -> ObservabilityResult<Vec<MetricDataPoint>> {
+            Ok(Vec::new())
+        }
+
+        async fn list_metrics(&self, _namespace: Option<String>) -> ObservabilityResult<Vec<MetricDefinition>> {
+            Ok(Vec::new())
+        }
+
+        async fn delete_metric(&self, _name: &str, _namespace: &str) -> ObservabilityResult<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_publishes_backlog_with_dimensions() {
+        let backend = Arc::new(InMemoryQueueBackend::new());
+        backend.create_queue(queue("jobs", QueueEngine::RabbitMQ)).await.unwrap();
+        for i in 0..3 {
+            let message = Message {
+                id: format!("m-{}", i),
+                queue_id: "jobs".into(),
+                data: vec![0; 10],
+                attributes: HashMap::new(),
+                publish_time: Utc::now() - chrono::Duration::seconds(120),
+                delivery_count: 0,
+                scheduled_for: None,
+                correlation_id: None,
+                reply_to: None,
+            };
+            backend.send_message("jobs", message).await.unwrap();
+        }
+        let metrics = Arc::new(RecordingMetrics::default());
+        let publisher = LagPublisher::new(backend, metrics.clone());
+        let lag = publisher.publish().await.unwrap();
+        assert_eq!(lag.len(), 1);
+        assert_eq!(lag[0].messages_behind, 3);
+
+        let points = metrics.points.lock().unwrap();
+        let age = points.iter().find(|p| p.name == OLDEST_MESSAGE_AGE_METRIC).unwrap();
+        assert!(matches!(age.value, MetricValue::Single(v) if v >= 120.0));
+        assert_eq!(age.namespace, DEFAULT_LAG_NAMESPACE);
+        assert_eq!(age.dimensions[QUEUE_NAME_DIMENSION], "jobs-name");
+        assert_eq!(age.dimensions[SCOPE_DIMENSION], "queue");
+    }
+}
@@ -0,0 +1,571 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use tokio::sync::{Mutex, Notify};
+use tokio::time::Instant;
+
+use crate::error::{DataError, DataResult};
+use super::{MessageOperations, Message, Queue, QueueManager, QueueMetrics, QueueTail, TailBatch, TailedMessage};
+
+struct StoredMessage {
+    message: Message,
+    visible_at: Instant,
+}
+
+// Matches the SQS ceiling on visibility timeouts
+pub const MAX_VISIBILITY_TIMEOUT_SECONDS: i64 = 12 * 60 * 60;
+
+#[derive(Default)]
+struct QueueState {
+    queue: Option<Queue>,
+    messages: VecDeque<StoredMessage>,
+    // Ordered by release time; the send sequence keeps messages due at the same instant in send order
+    scheduled: BTreeMap<(DateTime<Utc>, u64), Message>,
+    // Fan-out log read by QueueTail; consumers never touch it
+    log: VecDeque<TailedMessage>,
+    next_position: u64,
+}
+
+impl QueueState {
+    fn release_due(&mut self, now: DateTime<Utc>) {
+        if self.scheduled.first_key_value().is_none_or(|((at, _), _)| *at > now) {
+            return;
+        }
+        let later = self.scheduled.split_off(&(now, u64::MAX));
+        let due = std::mem::replace(&mut self.scheduled, later);
+        let visible_at = Instant::now();
+        self.messages
+            .extend(due.into_values().map(|message| StoredMessage { message, visible_at }));
+    }
+
+    // How long a receiver may sleep before something could become visible
+    fn next_change(&self, now: Instant, wall_now: DateTime<Utc>) -> Option<Duration> {
+        let release = self
+            .scheduled
+            .first_key_value()
+            .map(|((at, _), _)| (*at - wall_now).to_std().unwrap_or(Duration::ZERO));
+        let redelivery = self
+            .messages
+            .iter()
+            .filter(|s| s.visible_at > now)
+            .map(|s| s.visible_at - now)
+            .min();
+        release.into_iter().chain(redelivery).min()
+    }
+}
+
+// Process-local queue backend with SQS-style visibility timeouts, used for tests and local runs
+pub struct InMemoryQueueBackend {
+    queues: Mutex<HashMap<String, QueueState>>,
+    notify: Notify,
+    visibility_timeout: Duration,
+    max_delay: chrono::Duration,
+    sequence: AtomicU64,
+    tail_retention: usize,
+}
+
+impl Default for InMemoryQueueBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InMemoryQueueBackend {
+    pub fn new() -> Self {
+        Self {
+            queues: Mutex::new(HashMap::new()),
+            notify: Notify::new(),
+            visibility_timeout: Duration::from_secs(30),
+            max_delay: chrono::Duration::minutes(15),
+            sequence: AtomicU64::new(0),
+            tail_retention: 10_000,
+        }
+    }
+
+    pub fn with_visibility_timeout(mut self, timeout: Duration) -> Self {
+        self.visibility_timeout = timeout;
+        self
+    }
+
+    // Applies to queues that were never registered through create_queue; registered queues use
+    // their engine's limit
+    pub fn with_max_delay(mut self, max_delay: chrono::Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    // Messages kept per queue for tail readers
+    pub fn with_tail_retention(mut self, retention: usize) -> Self {
+        self.tail_retention = retention;
+        self
+    }
+
+    fn is_visible(stored: &StoredMessage, now: Instant) -> bool {
+        stored.visible_at <= now
+    }
+}
+
+#[async_trait]
+impl QueueManager for InMemoryQueueBackend {
+    async fn create_queue(&self, queue: Queue) -> DataResult<Queue> {
+        let mut queues = self.queues.lock().await;
+        let state = queues.entry(queue.id.clone()).or_default();
+        if state.queue.is_some() {
+            return Err(DataError::Conflict(format!("Queue {} already exists", queue.id)));
+        }
+        state.queue = Some(queue.clone());
+        Ok(queue)
+    }
+
+    async fn modify_queue(&self, queue: Queue) -> DataResult<Queue> {
+        let mut queues = self.queues.lock().await;
+        let state = queues
+            .get_mut(&queue.id)
+            .filter(|s| s.queue.is_some())
+            .ok_or_else(|| DataError::NotFound(format!("Queue {} not found", queue.id)))?;
+        state.queue = Some(queue.clone());
+        Ok(queue)
+    }
+
+    async fn delete_queue(&self, id: &str) -> DataResult<()> {
+        self.queues
+            .lock()
+            .await
+            .remove(id)
+            .map(|_| ())
+            .ok_or_else(|| DataError::NotFound(format!("Queue {} not found", id)))
+    }
+
+    async fn get_queue(&self, id: &str) -> DataResult<Queue> {
+        self.queues
+            .lock()
+            .await
+            .get(id)
+            .and_then(|s| s.queue.clone())
+            .ok_or_else(|| DataError::NotFound(format!("Queue {} not found", id)))
+    }
+
+    async fn list_queues(&self) -> DataResult<Vec<Queue>> {
+        let mut queues: Vec<Queue> = self
+            .queues
+            .lock()
+            .await
+            .values()
+            .filter_map(|s| s.queue.clone())
+            .collect();
+        queues.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(queues)
+    }
+
+    async fn purge_queue(&self, id: &str) -> DataResult<()> {
+        if let Some(state) = self.queues.lock().await.get_mut(id) {
+            state.messages.clear();
+            state.scheduled.clear();
+        }
+        Ok(())
+    }
+
+    async fn get_metrics(&self, id: &str, _window: chrono::Duration) -> DataResult<Vec<QueueMetrics>> {
+        let mut queues = self.queues.lock().await;
+        let state = queues
+            .get_mut(id)
+            .ok_or_else(|| DataError::NotFound(format!("Queue {} not found", id)))?;
+
+        let now = Instant::now();
+        let wall_now = Utc::now();
+        state.release_due(wall_now);
+        let mut metrics = QueueMetrics {
+            queue_id: id.to_string(),
+            timestamp: wall_now,
+            messages_available: 0,
+            messages_in_flight: 0,
+            messages_delayed: 0,
+            oldest_message_age_seconds: 0,
+            size_bytes: 0,
+            throughput_per_second: 0.0,
+            consumer_groups: Vec::new(),
+        };
+        for stored in &state.messages {
+            if stored.visible_at > now {
+                metrics.messages_in_flight += 1;
+            } else {
+                metrics.messages_available += 1;
+            }
+        }
+        metrics.messages_delayed = state.scheduled.len() as i64;
+        for message in state.messages.iter().map(|s| &s.message).chain(state.scheduled.values()) {
+            metrics.size_bytes += message.data.len() as i64;
+            metrics.oldest_message_age_seconds = metrics
+                .oldest_message_age_seconds
+                .max((wall_now - message.publish_time).num_seconds());
+        }
+        Ok(vec![metrics])
+    }
+}
+
+#[async_trait]
+impl MessageOperations for InMemoryQueueBackend {
+    async fn send_message(&self, queue_id: &str, mut message: Message) -> DataResult<String> {
+        if message.id.is_empty() {
+            message.id = uuid::Uuid::new_v4().to_string();
+        }
+        message.queue_id = queue_id.to_string();
+        let id = message.id.clone();
+
+        let mut queues = self.queues.lock().await;
+        let state = queues.entry(queue_id.to_string()).or_default();
+        if self.tail_retention > 0 {
+            state.next_position += 1;
+            state.log.push_back(TailedMessage {
+                position: state.next_position,
+                message: message.clone(),
+            });
+            while state.log.len() > self.tail_retention {
+                state.log.pop_front();
+            }
+        }
+        match message.scheduled_for.filter(|at| *at > Utc::now()) {
+            Some(at) => {
+                let sequence = self.sequence.fetch_add(1, Ordering::Relaxed);
+                state.scheduled.insert((at, sequence), message);
+            }
+            None => state.messages.push_back(StoredMessage {
+                message,
+                visible_at: Instant::now(),
+            }),
+        }
+        // Scheduled sends wake receivers too, so they can shorten their sleep to the release time
+        self.notify.notify_waiters();
+        Ok(id)
+    }
+
+    async fn send_batch(&self, queue_id: &str, messages: Vec<Message>) -> DataResult<Vec<String>> {
+        let mut ids = Vec::with_capacity(messages.len());
+        for message in messages {
+            ids.push(self.send_message(queue_id, message).await?);
+        }
+        Ok(ids)
+    }
+
+    async fn receive_messages(&self, queue_id: &str, max_messages: i32, wait_time_seconds: i32) -> DataResult<Vec<Message>> {
+        let deadline = Instant::now() + Duration::from_secs(wait_time_seconds.max(0) as u64);
+        loop {
+            let notified = self.notify.notified();
+            {
+                let mut queues = self.queues.lock().await;
+                if let Some(state) = queues.get_mut(queue_id) {
+                    state.release_due(Utc::now());
+                    let now = Instant::now();
+                    let mut received = Vec::new();
+                    for stored in state.messages.iter_mut() {
+                        if received.len() >= max_messages.max(1) as usize {
+                            break;
+                        }
+                        if Self::is_visible(stored, now) {
+                            stored.visible_at = now + self.visibility_timeout;
+                            stored.message.delivery_count += 1;
+                            received.push(stored.message.clone());
+                        }
+                    }
+                    if !received.is_empty() {
+                        return Ok(received);
+                    }
+                }
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(Vec::new());
+            }
+            // Wake on new messages or when the next scheduled release or redelivery is due
+            let next_change = self
+                .queues
+                .lock()
+                .await
+                .get(queue_id)
+                .and_then(|state| state.next_change(now, Utc::now()));
+            let sleep = next_change.into_iter().fold(deadline - now, Duration::min);
+            let _ = tokio::time::timeout(sleep, notified).await;
+        }
+    }
+
+    async fn delete_message(&self, queue_id: &str, message_id: &str) -> DataResult<()> {
+        let mut queues = self.queues.lock().await;
+        let state = queues
+            .get_mut(queue_id)
+            .ok_or_else(|| DataError::NotFound(format!("Queue {} not found", queue_id)))?;
+        if let Some(position) = state.messages.iter().position(|s| s.message.id == message_id) {
+            state.messages.remove(position);
+            return Ok(());
+        }
+        // Deleting a scheduled message cancels it
+        let key = state
+            .scheduled
+            .iter()
+            .find(|(_, m)| m.id == message_id)
+            .map(|(key, _)| *key)
+            .ok_or_else(|| DataError::NotFound(format!("Message {} not found", message_id)))?;
+        state.scheduled.remove(&key);
+        Ok(())
+    }
+
+    async fn peek_messages(&self, queue_id: &str, count: i32) -> DataResult<Vec<Message>> {
+        let queues = self.queues.lock().await;
+        let now = Instant::now();
+        Ok(queues
+            .get(queue_id)
+            .map(|state| {
+                state
+                    .messages
+                    .iter()
+                    .filter(|s| Self::is_visible(s, now))
+                    .take(count.max(0) as usize)
+                    .map(|s| s.message.clone())
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    async fn change_visibility(&self, queue_id: &str, message_id: &str, delay: chrono::Duration) -> DataResult<()> {
+        if delay < chrono::Duration::zero() || delay.num_seconds() > MAX_VISIBILITY_TIMEOUT_SECONDS {
+            return Err(DataError::Validation(format!(
+                "Visibility delay must be between 0 and {} seconds",
+                MAX_VISIBILITY_TIMEOUT_SECONDS
+            )));
+        }
+        let mut queues = self.queues.lock().await;
+        let state = queues
+            .get_mut(queue_id)
+            .ok_or_else(|| DataError::NotFound(format!("Queue {} not found", queue_id)))?;
+        let now = Instant::now();
+        let stored = state
+            .messages
+            .iter_mut()
+            .find(|s| s.message.id == message_id)
+            .ok_or_else(|| DataError::NotFound(format!("Message {} not found", message_id)))?;
+        if stored.visible_at <= now {
+            return Err(DataError::Validation(format!("Message {} is not in flight", message_id)));
+        }
+        stored.visible_at = now + delay.to_std().unwrap_or(Duration::ZERO);
+        self.notify.notify_waiters();
+        Ok(())
+    }
+
+    async fn send_scheduled(&self, queue_id: &str, mut message: Message, deliver_at: DateTime<Utc>) -> DataResult<String> {
+        let engine = self
+            .queues
+            .lock()
+            .await
+            .get(queue_id)
+            .and_then(|state| state.queue.as_ref())
+            .map(|queue| queue.engine.clone());
+        let max_delay = match &engine {
+            Some(engine) => engine.max_delivery_delay().ok_or_else(|| {
+                DataError::Validation(format!("{:?} queues do not support scheduled delivery", engine))
+            })?,
+            None => self.max_delay,
+        };
+        let delay = deliver_at - Utc::now();
+        if delay > max_delay {
+            return Err(DataError::Validation(format!(
+                "Delivery delay of {}s for queue {} exceeds the maximum of {}s",
+                delay.num_seconds(),
+                queue_id,
+                max_delay.num_seconds()
+            )));
+        }
+        message.scheduled_for = Some(deliver_at);
+        self.send_message(queue_id, message).await
+    }
+
+    async fn list_scheduled(&self, queue_id: &str) -> DataResult<Vec<Message>> {
+        let mut queues = self.queues.lock().await;
+        Ok(queues
+            .get_mut(queue_id)
+            .map(|state| {
+                state.release_due(Utc::now());
+                state.scheduled.values().cloned().collect()
+            })
+            .unwrap_or_default())
+    }
+}
+
+#[async_trait]
+impl QueueTail for InMemoryQueueBackend {
+    async fn tail(&self, queue_id: &str, after: u64, max_messages: usize) -> DataResult<TailBatch> {
+        let queues = self.queues.lock().await;
+        let Some(state) = queues.get(queue_id) else {
+            return Ok(TailBatch {
+                first_position: 1,
+                messages: Vec::new(),
+            });
+        };
+        Ok(TailBatch {
+            first_position: state.log.front().map(|t| t.position).unwrap_or(state.next_position + 1),
+            messages: state
+                .log
+                .iter()
+                .skip_while(|t| t.position <= after)
+                .take(max_messages)
+                .cloned()
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::queue::{DeliveryMode, DurabilityLevel, QueueConfig, QueueEngine, QueueStatus};
+
+    fn message(body: &str) -> Message {
+        Message {
+            id: String::new(),
+            queue_id: String::new(),
+            data: body.as_bytes().to_vec(),
+            attributes: HashMap::new(),
+            publish_time: Utc::now(),
+            delivery_count: 0,
+            scheduled_for: None,
+            correlation_id: None,
+            reply_to: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_receive_hides_until_visibility_timeout() {
+        let backend = InMemoryQueueBackend::new().with_visibility_timeout(Duration::from_millis(50));
+        backend.send_message("jobs", message("a")).await.unwrap();
+
+        let first = backend.receive_messages("jobs", 10, 0).await.unwrap();
+        assert_eq!(first.len(), 1);
+        assert_eq!(first[0].delivery_count, 1);
+        assert!(backend.receive_messages("jobs", 10, 0).await.unwrap().is_empty());
+
+        let redelivered = backend.receive_messages("jobs", 10, 1).await.unwrap();
+        assert_eq!(redelivered[0].id, first[0].id);
+        assert_eq!(redelivered[0].delivery_count, 2);
+
+        backend.delete_message("jobs", &first[0].id).await.unwrap();
+        assert!(backend.peek_messages("jobs", 10).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_long_poll_wakes_on_send() {
+        let backend = std::sync::Arc::new(InMemoryQueueBackend::new());
+        let receiver = {
+            let backend = backend.clone();
+            tokio::spawn(async move { backend.receive_messages("jobs", 1, 5).await })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        backend.send_message("jobs", message("wake")).await.unwrap();
+
+        let received = receiver.await.unwrap().unwrap();
+        assert_eq!(received[0].data, b"wake");
+    }
+
+    fn queue(id: &str, engine: QueueEngine) -> Queue {
+        Queue {
+            id: id.into(),
+            name: id.into(),
+            engine,
+            config: QueueConfig {
+                max_size_gb: 1,
+                message_retention_days: 4,
+                durability: DurabilityLevel::Memory,
+                delivery_mode: DeliveryMode::AtLeastOnce,
+                max_message_size_kb: 256,
+                supports_partitioning: false,
+                partition_count: None,
+                replication_factor: 1,
+                dead_letter_queue: None,
+                encryption: None,
+            },
+            status: QueueStatus::Active,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            tags: HashMap::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scheduled_release_accuracy() {
+        let backend = std::sync::Arc::new(InMemoryQueueBackend::new());
+        let start = Utc::now();
+        // Sent out of order so the release order comes from the schedule, not the send order
+        for i in (0..50).rev() {
+            let at = start + chrono::Duration::milliseconds(150 + i * 10);
+            backend.send_scheduled("jobs", message(&i.to_string()), at).await.unwrap();
+        }
+        assert_eq!(backend.list_scheduled("jobs").await.unwrap().len(), 50);
+        let metrics = backend.get_metrics("jobs", chrono::Duration::minutes(5)).await.unwrap();
+        assert_eq!((metrics[0].messages_delayed, metrics[0].messages_available), (50, 0));
+        assert!(backend.receive_messages("jobs", 10, 0).await.unwrap().is_empty());
+
+        let mut order = Vec::new();
+        while order.len() < 50 {
+            for received in backend.receive_messages("jobs", 1, 2).await.unwrap() {
+                let lateness = Utc::now() - received.scheduled_for.unwrap();
+                assert!(lateness >= chrono::Duration::zero(), "released {}ms early", -lateness.num_milliseconds());
+                assert!(lateness < chrono::Duration::milliseconds(50), "released {}ms late", lateness.num_milliseconds());
+                order.push(String::from_utf8(received.data).unwrap().parse::<i64>().unwrap());
+                backend.delete_message("jobs", &received.id).await.unwrap();
+            }
+        }
+        assert_eq!(order, (0..50).collect::<Vec<_>>());
+        let metrics = backend.get_metrics("jobs", chrono::Duration::minutes(5)).await.unwrap();
+        assert_eq!(metrics[0].messages_delayed, 0);
+    }
+
+    #[tokio::test]
+    async fn test_schedule_limits_and_past_times() {
+        let backend = InMemoryQueueBackend::new();
+        let past = Utc::now() - chrono::Duration::seconds(30);
+        backend.send_scheduled("jobs", message("late"), past).await.unwrap();
+        assert!(backend.list_scheduled("jobs").await.unwrap().is_empty());
+        assert_eq!(backend.receive_messages("jobs", 10, 0).await.unwrap()[0].data, b"late");
+
+        let too_far = Utc::now() + chrono::Duration::minutes(20);
+        let err = backend.send_scheduled("jobs", message("x"), too_far).await.unwrap_err();
+        assert!(matches!(&err, DataError::Validation(msg) if msg.contains("maximum of 900s")));
+
+        backend.create_queue(queue("rabbit", QueueEngine::RabbitMQ)).await.unwrap();
+        backend.create_queue(queue("events", QueueEngine::Kafka)).await.unwrap();
+        let in_a_day = Utc::now() + chrono::Duration::days(1);
+        let id = backend.send_scheduled("rabbit", message("x"), in_a_day).await.unwrap();
+        let far = Utc::now() + chrono::Duration::days(60);
+        assert!(backend.send_scheduled("rabbit", message("x"), far).await.is_err());
+        let err = backend.send_scheduled("events", message("x"), in_a_day).await.unwrap_err();
+        assert!(matches!(&err, DataError::Validation(msg) if msg.contains("do not support")));
+
+        // Deleting a scheduled message cancels it
+        backend.delete_message("rabbit", &id).await.unwrap();
+        assert!(backend.list_scheduled("rabbit").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_change_visibility() {
+        let backend = InMemoryQueueBackend::new();
+        let id = backend.send_message("jobs", message("a")).await.unwrap();
+        let err = backend.change_visibility("jobs", &id, chrono::Duration::seconds(1)).await.unwrap_err();
+        assert!(matches!(err, DataError::Validation(_)));
+
+        backend.receive_messages("jobs", 1, 0).await.unwrap();
+        backend.change_visibility("jobs", &id, chrono::Duration::milliseconds(200)).await.unwrap();
+        let started = Instant::now();
+        let redelivered = backend.receive_messages("jobs", 1, 2).await.unwrap();
+        let waited = started.elapsed();
+        assert_eq!(redelivered[0].delivery_count, 2);
+        assert!(waited >= Duration::from_millis(190) && waited < Duration::from_millis(300), "{:?}", waited);
+
+        backend.change_visibility("jobs", &id, chrono::Duration::zero()).await.unwrap();
+        assert_eq!(backend.receive_messages("jobs", 1, 0).await.unwrap().len(), 1);
+        assert!(backend
+            .change_visibility("jobs", &id, chrono::Duration::hours(13))
+            .await
+            .is_err());
+    }
+}
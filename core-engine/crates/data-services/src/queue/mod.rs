@@ -5,6 +5,20 @@ use chrono::{DateTime, Utc};
 
 use crate::error::DataResult;
 
+pub mod encryption;
+pub mod lag;
+pub mod memory;
+pub mod replay;
+pub mod replication;
+pub mod triggers;
+
+pub use encryption::{EncryptedMessageOperations, EncryptionStats};
+pub use lag::{consumer_lag, ConsumerLag, LagPublisher};
+pub use memory::InMemoryQueueBackend;
+pub use replay::{DlqReplayer, InMemoryReplayCheckpointStore, ReplayOptions, ReplayReport, TransformSpec};
+pub use replication::{QueueReplication, ReplicationWorker};
+pub use triggers::QueueEventSource;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Queue {
     pub id: String,
@@ -26,6 +40,18 @@ pub enum QueueEngine {
     GooglePubSub,
 }
 
+impl QueueEngine {
+    // Longest delivery delay the engine can honour; None when it has no delayed delivery.
+    // RabbitMQ's delayed-message exchange stores the delay as u32 milliseconds.
+    pub fn max_delivery_delay(&self) -> Option<chrono::Duration> {
+        match self {
+            QueueEngine::RabbitMQ => Some(chrono::Duration::milliseconds(u32::MAX as i64)),
+            QueueEngine::ActiveMQ | QueueEngine::AmazonMQ => Some(chrono::Duration::days(365)),
+            QueueEngine::Kafka | QueueEngine::GooglePubSub => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QueueConfig {
     pub max_size_gb: i32,
@@ -37,6 +63,14 @@ pub struct QueueConfig {
     pub partition_count: Option<i32>,
     pub replication_factor: i32,
     pub dead_letter_queue: Option<String>,
+    #[serde(default)]
+    pub encryption: Option<QueueEncryption>,
+}
+
+// Opt-in envelope encryption; `key_id` names a key-vault encryption key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueueEncryption {
+    pub key_id: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -85,6 +119,33 @@ pub struct QueueMetrics {
     pub oldest_message_age_seconds: i64,
     pub size_bytes: i64,
     pub throughput_per_second: f64,
+    // Kafka only: committed offsets against the log end, per consumer group
+    #[serde(default)]
+    pub consumer_groups: Vec<ConsumerGroupLag>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConsumerGroupLag {
+    pub group: String,
+    pub partitions: Vec<PartitionLag>,
+    // Publish age of the first uncommitted message across partitions, when the broker reports it
+    pub oldest_unconsumed_age_seconds: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PartitionLag {
+    pub partition: i32,
+    pub committed_offset: i64,
+    pub log_end_offset: i64,
+}
+
+impl ConsumerGroupLag {
+    pub fn total_lag(&self) -> i64 {
+        self.partitions
+            .iter()
+            .map(|p| (p.log_end_offset - p.committed_offset).max(0))
+            .sum()
+    }
 }
 
 #[async_trait]
@@ -105,6 +166,32 @@ pub trait MessageOperations: Send + Sync {
     async fn receive_messages(&self, queue_id: &str, max_messages: i32, wait_time_seconds: i32) -> DataResult<Vec<Message>>;
     async fn delete_message(&self, queue_id: &str, message_id: &str) -> DataResult<()>;
     async fn peek_messages(&self, queue_id: &str, count: i32) -> DataResult<Vec<Message>>;
+    // Pushes back redelivery of a received, not yet deleted message; zero makes it visible now
+    async fn change_visibility(&self, queue_id: &str, message_id: &str, delay: chrono::Duration) -> DataResult<()>;
+    // A time in the past delivers immediately
+    async fn send_scheduled(&self, queue_id: &str, message: Message, deliver_at: DateTime<Utc>) -> DataResult<String>;
+    async fn list_scheduled(&self, queue_id: &str) -> DataResult<Vec<Message>>;
+}
+
+#[derive(Debug, Clone)]
+pub struct TailedMessage {
+    pub position: u64,
+    pub message: Message,
+}
+
+#[derive(Debug, Clone)]
+pub struct TailBatch {
+    // Oldest position the backend still retains; a gap after the requested position means
+    // messages were evicted before they were read
+    pub first_position: u64,
+    pub messages: Vec<TailedMessage>,
+}
+
+// Read-only view of everything sent to a queue, independent of consumers (a dedicated consumer
+// group on Kafka, a fan-out binding on RabbitMQ). Positions start at 1.
+#[async_trait]
+pub trait QueueTail: Send + Sync {
+    async fn tail(&self, queue_id: &str, after: u64, max_messages: usize) -> DataResult<TailBatch>;
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -0,0 +1,664 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+use tracing::{debug, info, warn};
+
+use crate::error::{DataError, DataResult};
+use super::replication::MessageFilter;
+use super::{Message, MessageOperations};
+
+// Set on every replayed copy to the DLQ it came from
+pub const REPLAYED_FROM_ATTRIBUTE: &str = "x-sirsi-replayed-from";
+// How many times the message has been replayed; the loop guard compares against it
+pub const REPLAY_COUNT_ATTRIBUTE: &str = "x-sirsi-replay-count";
+
+pub const DEFAULT_MAX_REPLAY_COUNT: u32 = 3;
+
+// Fixes a message before it is re-sent. Returning an error leaves the message in the DLQ.
+pub trait MessageTransform: Send + Sync {
+    fn transform(&self, message: &mut Message) -> DataResult<()>;
+}
+
+impl<F> MessageTransform for F
+where
+    F: Fn(&mut Message) -> DataResult<()> + Send + Sync,
+{
+    fn transform(&self, message: &mut Message) -> DataResult<()> {
+        self(message)
+    }
+}
+
+// RFC 6902 subset applied to JSON payloads
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum PatchOperation {
+    Add { path: String, value: Value },
+    Remove { path: String },
+    Replace { path: String, value: Value },
+    // Fails the patch, and so skips the message, unless the value at `path` equals `value`
+    Test { path: String, value: Value },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum TransformSpec {
+    // A transform registered on the replayer under this name
+    Registered(String),
+    JsonPatch(Vec<PatchOperation>),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayOptions {
+    // Resumes the replay with this id; defaults to `<dlq>-><target>`
+    pub replay_id: Option<String>,
+    // Same syntax as replication filters, over message attributes
+    pub filter_expression: Option<String>,
+    pub transform: Option<TransformSpec>,
+    pub max_replay_count: u32,
+    pub dry_run: bool,
+    pub max_messages_per_second: Option<f64>,
+    pub max_messages: Option<u64>,
+    pub batch_size: i32,
+}
+
+impl Default for ReplayOptions {
+    fn default() -> Self {
+        Self {
+            replay_id: None,
+            filter_expression: None,
+            transform: None,
+            max_replay_count: DEFAULT_MAX_REPLAY_COUNT,
+            dry_run: false,
+            max_messages_per_second: None,
+            max_messages: None,
+            batch_size: 10,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReplayCheckpoint {
+    pub replay_id: String,
+    pub dlq_id: String,
+    pub target_queue_id: String,
+    // Sent to the target but not yet deleted from the DLQ; finished on resume without re-sending
+    pub pending_delete: Vec<String>,
+    // Left in the DLQ by the filter, loop guard or transform
+    pub skipped: HashSet<String>,
+    pub replayed: u64,
+    pub filtered: u64,
+    pub over_limit: u64,
+    pub failed_transform: u64,
+    pub completed: bool,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[async_trait]
+pub trait ReplayCheckpointStore: Send + Sync {
+    async fn load(&self, replay_id: &str) -> DataResult<Option<ReplayCheckpoint>>;
+    async fn save(&self, checkpoint: &ReplayCheckpoint) -> DataResult<()>;
+}
+
+#[derive(Default)]
+pub struct InMemoryReplayCheckpointStore {
+    checkpoints: Mutex<HashMap<String, ReplayCheckpoint>>,
+}
+
+impl InMemoryReplayCheckpointStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl ReplayCheckpointStore for InMemoryReplayCheckpointStore {
+    async fn load(&self, replay_id: &str) -> DataResult<Option<ReplayCheckpoint>> {
+        Ok(self.checkpoints.lock().await.get(replay_id).cloned())
+    }
+
+    async fn save(&self, checkpoint: &ReplayCheckpoint) -> DataResult<()> {
+        self.checkpoints
+            .lock()
+            .await
+            .insert(checkpoint.replay_id.clone(), checkpoint.clone());
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayReport {
+    pub replay_id: String,
+    pub dry_run: bool,
+    pub resumed: bool,
+    // In a dry run, how many messages would have been replayed
+    pub replayed: u64,
+    pub filtered: u64,
+    pub over_limit: u64,
+    pub failed_transform: u64,
+}
+
+impl ReplayReport {
+    fn from_checkpoint(checkpoint: &ReplayCheckpoint, dry_run: bool, resumed: bool) -> Self {
+        Self {
+            replay_id: checkpoint.replay_id.clone(),
+            dry_run,
+            resumed,
+            replayed: checkpoint.replayed,
+            filtered: checkpoint.filtered,
+            over_limit: checkpoint.over_limit,
+            failed_transform: checkpoint.failed_transform,
+        }
+    }
+}
+
+fn patch_error(msg: impl std::fmt::Display) -> DataError {
+    DataError::Validation(format!("JSON patch failed: {}", msg))
+}
+
+// Splits a JSON pointer into its parent pointer and unescaped last token
+fn split_pointer(path: &str) -> DataResult<(String, String)> {
+    let (parent, last) = path
+        .rsplit_once('/')
+        .ok_or_else(|| patch_error(format!("invalid path {}", path)))?;
+    Ok((parent.to_string(), last.replace("~1", "/").replace("~0", "~")))
+}
+
+fn apply_patch(document: &mut Value, operations: &[PatchOperation]) -> DataResult<()> {
+    for operation in operations {
+        match operation {
+            PatchOperation::Test { path, value } => {
+                if document.pointer(path) != Some(value) {
+                    return Err(patch_error(format!("test at {} did not match", path)));
+                }
+            }
+            PatchOperation::Replace { path, value } => {
+                *document
+                    .pointer_mut(path)
+                    .ok_or_else(|| patch_error(format!("nothing to replace at {}", path)))? = value.clone();
+            }
+            PatchOperation::Add { path, .. } | PatchOperation::Remove { path } if path.is_empty() => {
+                return Err(patch_error("add and remove cannot target the whole document"));
+            }
+            PatchOperation::Add { path, value } => {
+                let (parent, key) = split_pointer(path)?;
+                match document.pointer_mut(&parent) {
+                    Some(Value::Object(map)) => {
+                        map.insert(key, value.clone());
+                    }
+                    Some(Value::Array(items)) if key == "-" => items.push(value.clone()),
+                    Some(Value::Array(items)) => {
+                        let index = key.parse::<usize>().ok().filter(|i| *i <= items.len());
+                        let index = index.ok_or_else(|| patch_error(format!("bad array index in {}", path)))?;
+                        items.insert(index, value.clone());
+                    }
+                    _ => return Err(patch_error(format!("no container at {}", parent))),
+                }
+            }
+            PatchOperation::Remove { path } => {
+                let (parent, key) = split_pointer(path)?;
+                let removed = match document.pointer_mut(&parent) {
+                    Some(Value::Object(map)) => map.remove(&key).is_some(),
+                    Some(Value::Array(items)) => match key.parse::<usize>() {
+                        Ok(index) if index < items.len() => {
+                            items.remove(index);
+                            true
+                        }
+                        _ => false,
+                    },
+                    _ => false,
+                };
+                if !removed {
+                    return Err(patch_error(format!("nothing to remove at {}", path)));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+struct JsonPatch(Vec<PatchOperation>);
+
+impl MessageTransform for JsonPatch {
+    fn transform(&self, message: &mut Message) -> DataResult<()> {
+        let mut document: Value = serde_json::from_slice(&message.data).map_err(patch_error)?;
+        apply_patch(&mut document, &self.0)?;
+        message.data = serde_json::to_vec(&document).map_err(patch_error)?;
+        Ok(())
+    }
+}
+
+enum Verdict {
+    Replay(Message),
+    Filtered,
+    OverLimit,
+    FailedTransform,
+}
+
+// Moves messages from a dead-letter queue back to a working queue. Delivery is at-least-once:
+// a message is deleted from the DLQ only after the target accepted it, and the checkpoint
+// records sends whose delete has not happened yet so a resumed replay finishes them instead of
+// sending them again.
+pub struct DlqReplayer {
+    queues: Arc<dyn MessageOperations>,
+    checkpoints: Arc<dyn ReplayCheckpointStore>,
+    transforms: HashMap<String, Arc<dyn MessageTransform>>,
+}
+
+impl DlqReplayer {
+    pub fn new(queues: Arc<dyn MessageOperations>, checkpoints: Arc<dyn ReplayCheckpointStore>) -> Self {
+        Self {
+            queues,
+            checkpoints,
+            transforms: HashMap::new(),
+        }
+    }
+
+    pub fn with_transform(mut self, name: impl Into<String>, transform: Arc<dyn MessageTransform>) -> Self {
+        self.transforms.insert(name.into(), transform);
+        self
+    }
+
+    fn resolve_transform(&self, spec: &Option<TransformSpec>) -> DataResult<Option<Arc<dyn MessageTransform>>> {
+        Ok(match spec {
+            None => None,
+            Some(TransformSpec::Registered(name)) => {
+                let transform = self
+                    .transforms
+                    .get(name)
+                    .ok_or_else(|| DataError::Validation(format!("No replay transform registered as {}", name)))?;
+                Some(transform.clone())
+            }
+            Some(TransformSpec::JsonPatch(operations)) => Some(Arc::new(JsonPatch(operations.clone()))),
+        })
+    }
+
+    fn judge(
+        message: &Message,
+        dlq_id: &str,
+        target_queue_id: &str,
+        filter: &MessageFilter,
+        transform: Option<&dyn MessageTransform>,
+        max_replay_count: u32,
+    ) -> Verdict {
+        if !filter.matches(&message.attributes) {
+            return Verdict::Filtered;
+        }
+        let count = message
+            .attributes
+            .get(REPLAY_COUNT_ATTRIBUTE)
+            .and_then(|c| c.parse::<u32>().ok())
+            .unwrap_or(0);
+        if count >= max_replay_count {
+            return Verdict::OverLimit;
+        }
+        let mut replay = message.clone();
+        if let Some(transform) = transform {
+            if let Err(e) = transform.transform(&mut replay) {
+                debug!("Transform rejected message {} from {}: {}", message.id, dlq_id, e);
+                return Verdict::FailedTransform;
+            }
+        }
+        replay.queue_id = target_queue_id.to_string();
+        replay.delivery_count = 0;
+        replay
+            .attributes
+            .insert(REPLAYED_FROM_ATTRIBUTE.to_string(), dlq_id.to_string());
+        replay
+            .attributes
+            .insert(REPLAY_COUNT_ATTRIBUTE.to_string(), (count + 1).to_string());
+        Verdict::Replay(replay)
+    }
+
+    async fn save(&self, checkpoint: &mut ReplayCheckpoint, dry_run: bool) -> DataResult<()> {
+        if dry_run {
+            return Ok(());
+        }
+        checkpoint.updated_at = Some(Utc::now());
+        self.checkpoints.save(checkpoint).await
+    }
+
+    // Makes received-but-unprocessed messages visible again so neither this replay's resume nor
+    // other DLQ readers wait out the visibility timeout
+    async fn release(&self, dlq_id: &str, ids: impl IntoIterator<Item = &String>) {
+        for id in ids {
+            if let Err(e) = self.queues.change_visibility(dlq_id, id, chrono::Duration::zero()).await {
+                debug!("Could not release message {} on {}: {}", id, dlq_id, e);
+            }
+        }
+    }
+
+    pub async fn replay_dlq(&self, dlq_id: &str, target_queue_id: &str, options: ReplayOptions) -> DataResult<ReplayReport> {
+        if dlq_id == target_queue_id {
+            return Err(DataError::Validation(format!("Cannot replay {} into itself", dlq_id)));
+        }
+        let filter = match &options.filter_expression {
+            Some(expression) => MessageFilter::parse(expression)?,
+            None => MessageFilter::default(),
+        };
+        let transform = self.resolve_transform(&options.transform)?;
+        let replay_id = options
+            .replay_id
+            .clone()
+            .unwrap_or_else(|| format!("{}->{}", dlq_id, target_queue_id));
+
+        let previous = if options.dry_run { None } else { self.checkpoints.load(&replay_id).await? };
+        let resumed = previous.as_ref().is_some_and(|c| !c.completed);
+        let mut checkpoint = match previous {
+            Some(checkpoint) if !checkpoint.completed => checkpoint,
+            _ => ReplayCheckpoint {
+                replay_id: replay_id.clone(),
+                dlq_id: dlq_id.to_string(),
+                target_queue_id: target_queue_id.to_string(),
+                ..Default::default()
+            },
+        };
+        if checkpoint.dlq_id != dlq_id || checkpoint.target_queue_id != target_queue_id {
+            return Err(DataError::Conflict(format!(
+                "Replay {} is in progress from {} to {}",
+                replay_id, checkpoint.dlq_id, checkpoint.target_queue_id
+            )));
+        }
+        if resumed {
+            info!("Resuming replay {} after {} messages", replay_id, checkpoint.replayed);
+        }
+
+        // Finish deletes interrupted last time; these messages already reached the target
+        for id in std::mem::take(&mut checkpoint.pending_delete) {
+            match self.queues.delete_message(dlq_id, &id).await {
+                Ok(()) | Err(DataError::NotFound(_)) => checkpoint.replayed += 1,
+                Err(e) => {
+                    checkpoint.pending_delete.push(id);
+                    self.save(&mut checkpoint, false).await?;
+                    return Err(e);
+                }
+            }
+        }
+        self.save(&mut checkpoint, options.dry_run).await?;
+
+        let interval = options
+            .max_messages_per_second
+            .filter(|rate| *rate > 0.0)
+            .map(|rate| Duration::from_secs_f64(1.0 / rate));
+        let mut next_send = Instant::now();
+        let mut held = HashSet::new();
+        let limit = options.max_messages.unwrap_or(u64::MAX);
+        let mut handled: u64 = 0;
+
+        'batches: while handled < limit {
+            let batch = self.queues.receive_messages(dlq_id, options.batch_size.max(1), 0).await?;
+            if batch.is_empty() {
+                break;
+            }
+            held.extend(batch.iter().filter(|m| checkpoint.skipped.contains(&m.id)).map(|m| m.id.clone()));
+            // Only messages this replay already decided to leave behind are still visible
+            if batch.iter().all(|m| checkpoint.skipped.contains(&m.id)) {
+                break;
+            }
+
+            for (index, message) in batch.iter().enumerate() {
+                if checkpoint.skipped.contains(&message.id) {
+                    continue;
+                }
+                if handled >= limit {
+                    self.release(dlq_id, batch[index..].iter().map(|m| &m.id)).await;
+                    break 'batches;
+                }
+                handled += 1;
+                let verdict = Self::judge(
+                    message,
+                    dlq_id,
+                    target_queue_id,
+                    &filter,
+                    transform.as_deref(),
+                    options.max_replay_count,
+                );
+                let replay = match verdict {
+                    Verdict::Replay(replay) => replay,
+                    skipped => {
+                        match skipped {
+                            Verdict::Filtered => checkpoint.filtered += 1,
+                            Verdict::OverLimit => {
+                                warn!("Message {} in {} hit the replay limit of {}", message.id, dlq_id, options.max_replay_count);
+                                checkpoint.over_limit += 1
+                            }
+                            _ => checkpoint.failed_transform += 1,
+                        }
+                        checkpoint.skipped.insert(message.id.clone());
+                        held.insert(message.id.clone());
+                        continue;
+                    }
+                };
+                if options.dry_run {
+                    checkpoint.replayed += 1;
+                    checkpoint.skipped.insert(message.id.clone());
+                    held.insert(message.id.clone());
+                    continue;
+                }
+
+                if let Some(interval) = interval {
+                    tokio::time::sleep_until(next_send).await;
+                    next_send = Instant::now().max(next_send) + interval;
+                }
+                if let Err(e) = self.queues.send_message(target_queue_id, replay).await {
+                    warn!("Replay {} stopped: sending {} to {} failed: {}", replay_id, message.id, target_queue_id, e);
+                    self.release(dlq_id, batch[index..].iter().map(|m| &m.id).chain(&held)).await;
+                    self.save(&mut checkpoint, false).await?;
+                    return Err(e);
+                }
+                checkpoint.pending_delete.push(message.id.clone());
+                self.save(&mut checkpoint, false).await?;
+                if let Err(e) = self.queues.delete_message(dlq_id, &message.id).await {
+                    warn!("Replay {} stopped: deleting {} from {} failed: {}", replay_id, message.id, dlq_id, e);
+                    self.release(dlq_id, batch[index + 1..].iter().map(|m| &m.id).chain(&held)).await;
+                    return Err(e);
+                }
+                checkpoint.pending_delete.clear();
+                checkpoint.replayed += 1;
+                self.save(&mut checkpoint, false).await?;
+            }
+        }
+
+        self.release(dlq_id, &held).await;
+        checkpoint.completed = true;
+        self.save(&mut checkpoint, options.dry_run).await?;
+        info!(
+            "Replay {} {}: {} replayed, {} filtered, {} over the replay limit, {} failed transformation",
+            replay_id,
+            if options.dry_run { "dry run finished" } else { "finished" },
+            checkpoint.replayed,
+            checkpoint.filtered,
+            checkpoint.over_limit,
+            checkpoint.failed_transform
+        );
+        Ok(ReplayReport::from_checkpoint(&checkpoint, options.dry_run, resumed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::queue::InMemoryQueueBackend;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn message(id: &str, data: Value, attributes: &[(&str, &str)]) -> Message {
+        Message {
+            id: id.into(),
+            queue_id: "orders-dlq".into(),
+            data: serde_json::to_vec(&data).unwrap(),
+            attributes: attributes.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            publish_time: Utc::now(),
+            delivery_count: 5,
+            scheduled_for: None,
+            correlation_id: None,
+            reply_to: None,
+        }
+    }
+
+    async fn drain(backend: &InMemoryQueueBackend, queue: &str) -> Vec<Message> {
+        let messages = backend.receive_messages(queue, 100, 0).await.unwrap();
+        for m in &messages {
+            backend.delete_message(queue, &m.id).await.unwrap();
+        }
+        messages
+    }
+
+    #[tokio::test]
+    async fn test_loop_guard_filter_and_transforms() {
+        let backend = Arc::new(InMemoryQueueBackend::new());
+        let dlq = "orders-dlq";
+        backend.send_message(dlq, message("bad-amount", json!({"amount": "12.50", "sku": "a"}), &[("tenant", "acme")])).await.unwrap();
+        backend.send_message(dlq, message("looping", json!({"amount": 1}), &[("tenant", "acme"), (REPLAY_COUNT_ATTRIBUTE, "3")])).await.unwrap();
+        backend.send_message(dlq, message("other-tenant", json!({"amount": 2}), &[("tenant", "globex")])).await.unwrap();
+        backend.send_message(dlq, message("not-json", json!(null), &[("tenant", "acme")])).await.unwrap();
+
+        let checkpoints = Arc::new(InMemoryReplayCheckpointStore::new());
+        let stamp = |m: &mut Message| -> DataResult<()> {
+            m.attributes.insert("fixed-by".into(), "ops".into());
+            Ok(())
+        };
+        let replayer = DlqReplayer::new(backend.clone(), checkpoints).with_transform("stamp", Arc::new(stamp));
+
+        // The numeric test rejects "not-json"'s null payload
+        let patch = vec![
+            PatchOperation::Test { path: "/amount".into(), value: json!("12.50") },
+            PatchOperation::Replace { path: "/amount".into(), value: json!(1250) },
+            PatchOperation::Add { path: "/currency".into(), value: json!("USD") },
+            PatchOperation::Remove { path: "/sku".into() },
+        ];
+        let options = ReplayOptions {
+            filter_expression: Some("tenant = 'acme'".into()),
+            transform: Some(TransformSpec::JsonPatch(patch)),
+            ..Default::default()
+        };
+        let dry = replayer
+            .replay_dlq(dlq, "orders", ReplayOptions { dry_run: true, ..options.clone() })
+            .await
+            .unwrap();
+        assert_eq!((dry.replayed, dry.filtered, dry.over_limit, dry.failed_transform), (1, 1, 1, 1));
+        assert!(backend.peek_messages("orders", 10).await.unwrap().is_empty());
+        assert_eq!(backend.peek_messages(dlq, 10).await.unwrap().len(), 4);
+
+        let report = replayer.replay_dlq(dlq, "orders", options).await.unwrap();
+        assert_eq!((report.replayed, report.filtered, report.over_limit, report.failed_transform), (1, 1, 1, 1));
+        let replayed = drain(&backend, "orders").await;
+        assert_eq!(replayed.len(), 1);
+        let fixed: Value = serde_json::from_slice(&replayed[0].data).unwrap();
+        assert_eq!(fixed, json!({"amount": 1250, "currency": "USD"}));
+        assert_eq!(replayed[0].attributes[REPLAYED_FROM_ATTRIBUTE], dlq);
+        assert_eq!(replayed[0].attributes[REPLAY_COUNT_ATTRIBUTE], "1");
+        assert_eq!(replayed[0].attributes["tenant"], "acme");
+        let left: HashSet<String> = backend.peek_messages(dlq, 10).await.unwrap().into_iter().map(|m| m.id).collect();
+        assert_eq!(left, HashSet::from(["looping".to_string(), "other-tenant".to_string(), "not-json".to_string()]));
+
+        // A registered transform by name under a tighter limit: the earlier replayed copy is
+        // stopped by the guard, and "not-json" goes through since this transform ignores payloads
+        let mut copy = replayed[0].clone();
+        copy.attributes.insert(REPLAY_COUNT_ATTRIBUTE.into(), "1".into());
+        backend.send_message(dlq, copy).await.unwrap();
+        let options = ReplayOptions {
+            replay_id: Some("second".into()),
+            filter_expression: Some("tenant = 'acme'".into()),
+            transform: Some(TransformSpec::Registered("stamp".into())),
+            max_replay_count: 1,
+            ..Default::default()
+        };
+        assert!(matches!(
+            replayer
+                .replay_dlq(dlq, "orders", ReplayOptions { transform: Some(TransformSpec::Registered("missing".into())), ..options.clone() })
+                .await,
+            Err(DataError::Validation(_))
+        ));
+        let report = replayer.replay_dlq(dlq, "orders", options).await.unwrap();
+        assert_eq!((report.replayed, report.filtered, report.over_limit), (1, 1, 2));
+        let replayed = drain(&backend, "orders").await;
+        assert_eq!(replayed[0].id, "not-json");
+        assert_eq!(replayed[0].attributes["fixed-by"], "ops");
+    }
+
+    // Fails the nth send or delete once
+    struct Faulty {
+        inner: Arc<InMemoryQueueBackend>,
+        sends: AtomicUsize,
+        deletes: AtomicUsize,
+        fail_send: usize,
+        fail_delete: usize,
+    }
+
+    #[async_trait]
+    impl MessageOperations for Faulty {
+        async fn send_message(&self, queue_id: &str, message: Message) -> DataResult<String> {
+            if self.sends.fetch_add(1, Ordering::SeqCst) + 1 == self.fail_send {
+                return Err(DataError::Unavailable("target offline".into()));
+            }
+            self.inner.send_message(queue_id, message).await
+        }
+        async fn send_batch(&self, queue_id: &str, messages: Vec<Message>) -> DataResult<Vec<String>> {
+            self.inner.send_batch(queue_id, messages).await
+        }
+        async fn receive_messages(&self, queue_id: &str, max_messages: i32, wait_time_seconds: i32) -> DataResult<Vec<Message>> {
+            self.inner.receive_messages(queue_id, max_messages, wait_time_seconds).await
+        }
+        async fn delete_message(&self, queue_id: &str, message_id: &str) -> DataResult<()> {
+            if self.deletes.fetch_add(1, Ordering::SeqCst) + 1 == self.fail_delete {
+                return Err(DataError::Unavailable("dlq offline".into()));
+            }
+            self.inner.delete_message(queue_id, message_id).await
+        }
+        async fn peek_messages(&self, queue_id: &str, count: i32) -> DataResult<Vec<Message>> {
+            self.inner.peek_messages(queue_id, count).await
+        }
+        async fn change_visibility(&self, queue_id: &str, message_id: &str, delay: chrono::Duration) -> DataResult<()> {
+            self.inner.change_visibility(queue_id, message_id, delay).await
+        }
+        async fn send_scheduled(&self, queue_id: &str, message: Message, deliver_at: DateTime<Utc>) -> DataResult<String> {
+            self.inner.send_scheduled(queue_id, message, deliver_at).await
+        }
+        async fn list_scheduled(&self, queue_id: &str) -> DataResult<Vec<Message>> {
+            self.inner.list_scheduled(queue_id).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mid_replay_failures_resume_without_loss_or_duplicates() {
+        let backend = Arc::new(InMemoryQueueBackend::new());
+        for i in 0..6 {
+            backend.send_message("dlq", message(&format!("m-{}", i), json!({"n": i}), &[])).await.unwrap();
+        }
+        // The 3rd send fails, then the 4th delete (m-4's, after its send succeeded)
+        let faulty = Arc::new(Faulty {
+            inner: backend.clone(),
+            sends: AtomicUsize::new(0),
+            deletes: AtomicUsize::new(0),
+            fail_send: 3,
+            fail_delete: 4,
+        });
+        let checkpoints = Arc::new(InMemoryReplayCheckpointStore::new());
+        let replayer = DlqReplayer::new(faulty, checkpoints.clone());
+        let options = ReplayOptions {
+            batch_size: 4,
+            max_messages_per_second: Some(1000.0),
+            ..Default::default()
+        };
+
+        assert!(replayer.replay_dlq("dlq", "work", options.clone()).await.is_err());
+        assert_eq!(checkpoints.load("dlq->work").await.unwrap().unwrap().replayed, 2);
+        assert!(replayer.replay_dlq("dlq", "work", options.clone()).await.is_err());
+        let checkpoint = checkpoints.load("dlq->work").await.unwrap().unwrap();
+        assert_eq!(checkpoint.pending_delete.len(), 1);
+
+        let report = replayer.replay_dlq("dlq", "work", options).await.unwrap();
+        assert!(report.resumed);
+        assert_eq!(report.replayed, 6);
+        let mut ids: Vec<String> = drain(&backend, "work").await.into_iter().map(|m| m.id).collect();
+        ids.sort();
+        assert_eq!(ids, (0..6).map(|i| format!("m-{}", i)).collect::<Vec<_>>());
+        assert!(backend.peek_messages("dlq", 10).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,538 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{watch, Mutex};
+use tracing::{info, warn};
+
+use crate::error::{DataError, DataResult};
+use super::{Message, MessageOperations, QueueEngine, QueueTail, TailedMessage};
+
+// Set on every replicated copy; messages carrying it are never replicated again, so a pair of
+// queues replicating into each other cannot loop
+pub const REPLICATED_FROM_ATTRIBUTE: &str = "x-sirsi-replicated-from";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueueReplication {
+    pub id: String,
+    pub source_queue: String,
+    pub target_queue: String,
+    pub target_engine: QueueEngine,
+    // `key = 'value'` / `key != 'value'` / `key` terms over message attributes, joined by AND
+    pub filter_expression: Option<String>,
+    pub lag_alarm_threshold_seconds: i64,
+    // Messages read from the source but not yet written to the target; reading pauses at the bound
+    pub max_buffered_messages: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Condition {
+    Equals(String, String),
+    NotEquals(String, String),
+    Exists(String),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MessageFilter {
+    conditions: Vec<Condition>,
+}
+
+impl MessageFilter {
+    pub fn parse(expression: &str) -> DataResult<Self> {
+        let mut conditions = Vec::new();
+        if expression.trim().is_empty() {
+            return Ok(Self { conditions });
+        }
+        for term in expression.split(" AND ") {
+            let term = term.trim();
+            let condition = if let Some((key, value)) = term.split_once("!=") {
+                Condition::NotEquals(Self::key(key, expression)?, Self::value(value, expression)?)
+            } else if let Some((key, value)) = term.split_once('=') {
+                Condition::Equals(Self::key(key, expression)?, Self::value(value, expression)?)
+            } else {
+                Condition::Exists(Self::key(term, expression)?)
+            };
+            conditions.push(condition);
+        }
+        Ok(Self { conditions })
+    }
+
+    fn key(raw: &str, expression: &str) -> DataResult<String> {
+        let key = raw.trim();
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c)) {
+            return Err(DataError::Validation(format!("Invalid filter expression: {}", expression)));
+        }
+        Ok(key.to_string())
+    }
+
+    fn value(raw: &str, expression: &str) -> DataResult<String> {
+        raw.trim()
+            .strip_prefix('\'')
+            .and_then(|v| v.strip_suffix('\''))
+            .map(str::to_string)
+            .ok_or_else(|| DataError::Validation(format!("Invalid filter expression: {}", expression)))
+    }
+
+    pub fn matches(&self, attributes: &HashMap<String, String>) -> bool {
+        self.conditions.iter().all(|condition| match condition {
+            Condition::Equals(key, value) => attributes.get(key) == Some(value),
+            Condition::NotEquals(key, value) => attributes.get(key) != Some(value),
+            Condition::Exists(key) => attributes.contains_key(key),
+        })
+    }
+}
+
+// Last source position written to the target, so a restarted worker resumes instead of
+// replaying the whole retained log
+#[async_trait]
+pub trait CheckpointStore: Send + Sync {
+    async fn load(&self, replication_id: &str) -> DataResult<Option<u64>>;
+    async fn save(&self, replication_id: &str, position: u64) -> DataResult<()>;
+}
+
+#[derive(Default)]
+pub struct InMemoryCheckpointStore {
+    positions: Mutex<HashMap<String, u64>>,
+}
+
+impl InMemoryCheckpointStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl CheckpointStore for InMemoryCheckpointStore {
+    async fn load(&self, replication_id: &str) -> DataResult<Option<u64>> {
+        Ok(self.positions.lock().await.get(replication_id).copied())
+    }
+
+    async fn save(&self, replication_id: &str, position: u64) -> DataResult<()> {
+        self.positions.lock().await.insert(replication_id.to_string(), position);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplicationStatus {
+    pub replication_id: String,
+    pub position: u64,
+    pub replicated: u64,
+    pub skipped: u64,
+    // Evicted from the source before they could be read
+    pub lost: u64,
+    pub buffered: usize,
+    pub lag_seconds: i64,
+    pub lag_alarm: bool,
+    pub target_healthy: bool,
+    pub last_error: Option<String>,
+}
+
+struct Pending {
+    entry: TailedMessage,
+    replicate: bool,
+}
+
+#[derive(Default)]
+struct WorkerState {
+    loaded: bool,
+    // Everything up to here is on the target (or deliberately skipped)
+    position: u64,
+    // Everything up to here is in the buffer
+    read_position: u64,
+    buffer: VecDeque<Pending>,
+    replicated: u64,
+    skipped: u64,
+    lost: u64,
+    target_down: bool,
+    last_error: Option<String>,
+}
+
+pub struct ReplicationWorker {
+    config: QueueReplication,
+    filter: MessageFilter,
+    source: Arc<dyn QueueTail>,
+    target: Arc<dyn MessageOperations>,
+    checkpoints: Arc<dyn CheckpointStore>,
+    batch_size: usize,
+    poll_interval: Duration,
+    state: Mutex<WorkerState>,
+}
+
+impl ReplicationWorker {
+    pub fn new(
+        config: QueueReplication,
+        source: Arc<dyn QueueTail>,
+        target: Arc<dyn MessageOperations>,
+        checkpoints: Arc<dyn CheckpointStore>,
+    ) -> DataResult<Self> {
+        if config.max_buffered_messages == 0 {
+            return Err(DataError::Validation(format!(
+                "Replication {} needs a non-zero buffer",
+                config.id
+            )));
+        }
+        let filter = match &config.filter_expression {
+            Some(expression) => MessageFilter::parse(expression)?,
+            None => MessageFilter::default(),
+        };
+        Ok(Self {
+            config,
+            filter,
+            source,
+            target,
+            checkpoints,
+            batch_size: 100,
+            poll_interval: Duration::from_millis(500),
+            state: Mutex::new(WorkerState::default()),
+        })
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    pub fn with_poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    pub fn config(&self) -> &QueueReplication {
+        &self.config
+    }
+
+    // Reads what fits in the buffer, then writes buffered messages to the target in source order.
+    // Returns how many messages reached the target; a target failure keeps the rest buffered.
+    pub async fn step(&self) -> DataResult<usize> {
+        let mut state = self.state.lock().await;
+        if !state.loaded {
+            let position = self.checkpoints.load(&self.config.id).await?.unwrap_or(0);
+            state.position = position;
+            state.read_position = position;
+            state.loaded = true;
+        }
+
+        let room = self.config.max_buffered_messages.saturating_sub(state.buffer.len());
+        if room > 0 {
+            let batch = self
+                .source
+                .tail(&self.config.source_queue, state.read_position, room.min(self.batch_size))
+                .await?;
+            if batch.first_position > state.read_position + 1 {
+                let missed = batch.first_position - state.read_position - 1;
+                warn!(
+                    "Replication {} missed {} messages evicted from {} before they were read",
+                    self.config.id, missed, self.config.source_queue
+                );
+                state.lost += missed;
+                state.read_position = batch.first_position - 1;
+                if state.buffer.is_empty() {
+                    state.position = state.read_position;
+                }
+            }
+            for entry in batch.messages {
+                state.read_position = entry.position;
+                let replicate = !entry.message.attributes.contains_key(REPLICATED_FROM_ATTRIBUTE)
+                    && self.filter.matches(&entry.message.attributes);
+                state.buffer.push_back(Pending { entry, replicate });
+            }
+        }
+
+        let committed = state.position;
+        let mut sent = 0;
+        let mut failure = None;
+        while let Some(pending) = state.buffer.front() {
+            let position = pending.entry.position;
+            if pending.replicate {
+                let message = self.replica(&pending.entry.message);
+                if let Err(e) = self.target.send_message(&self.config.target_queue, message).await {
+                    failure = Some(e);
+                    break;
+                }
+                state.replicated += 1;
+                sent += 1;
+            } else {
+                state.skipped += 1;
+            }
+            state.buffer.pop_front();
+            state.position = position;
+        }
+
+        if state.position != committed {
+            self.checkpoints.save(&self.config.id, state.position).await?;
+        }
+        match failure {
+            Some(e) => {
+                if !state.target_down {
+                    warn!(
+                        "Replication {} target {} unavailable, buffering: {}",
+                        self.config.id, self.config.target_queue, e
+                    );
+                }
+                state.target_down = true;
+                state.last_error = Some(e.to_string());
+                Err(e)
+            }
+            None => {
+                if state.target_down {
+                    info!(
+                        "Replication {} target {} recovered at position {}",
+                        self.config.id, self.config.target_queue, state.position
+                    );
+                }
+                state.target_down = false;
+                state.last_error = None;
+                Ok(sent)
+            }
+        }
+    }
+
+    fn replica(&self, message: &Message) -> Message {
+        let mut replica = message.clone();
+        replica.queue_id = self.config.target_queue.clone();
+        replica.delivery_count = 0;
+        replica
+            .attributes
+            .insert(REPLICATED_FROM_ATTRIBUTE.to_string(), self.config.source_queue.clone());
+        replica
+    }
+
+    pub async fn status(&self) -> ReplicationStatus {
+        let state = self.state.lock().await;
+        let lag_seconds = state
+            .buffer
+            .iter()
+            .find(|p| p.replicate)
+            .map(|p| (Utc::now() - p.entry.message.publish_time).num_seconds().max(0))
+            .unwrap_or(0);
+        ReplicationStatus {
+            replication_id: self.config.id.clone(),
+            position: state.position,
+            replicated: state.replicated,
+            skipped: state.skipped,
+            lost: state.lost,
+            buffered: state.buffer.len(),
+            lag_seconds,
+            lag_alarm: lag_seconds >= self.config.lag_alarm_threshold_seconds,
+            target_healthy: !state.target_down,
+            last_error: state.last_error.clone(),
+        }
+    }
+
+    pub async fn run(self: Arc<Self>, mut shutdown: watch::Receiver<bool>) -> DataResult<()> {
+        info!(
+            "Replication {} running from {} to {}",
+            self.config.id, self.config.source_queue, self.config.target_queue
+        );
+        loop {
+            tokio::select! {
+                result = self.step() => {
+                    match result {
+                        Ok(0) => tokio::time::sleep(self.poll_interval).await,
+                        Ok(_) => {}
+                        Err(e) => {
+                            warn!("Replication {} step failed: {}", self.config.id, e);
+                            tokio::time::sleep(Duration::from_secs(1)).await;
+                        }
+                    }
+                    let status = self.status().await;
+                    if status.lag_alarm {
+                        warn!(
+                            "Replication {} lag {}s exceeds {}s",
+                            self.config.id, status.lag_seconds, self.config.lag_alarm_threshold_seconds
+                        );
+                    }
+                }
+                _ = shutdown.changed() => {
+                    info!("Replication {} stopping", self.config.id);
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use chrono::{DateTime, Utc};
+
+    use crate::queue::InMemoryQueueBackend;
+
+    // Target that fails every call while `down` is set
+    struct FlakyTarget {
+        inner: InMemoryQueueBackend,
+        down: AtomicBool,
+    }
+
+    impl FlakyTarget {
+        fn check(&self) -> DataResult<()> {
+            if self.down.load(Ordering::SeqCst) {
+                return Err(DataError::Unavailable("target region offline".to_string()));
+            }
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl MessageOperations for FlakyTarget {
+        async fn send_message(&self, queue_id: &str, message: Message) -> DataResult<String> {
+            self.check()?;
+            self.inner.send_message(queue_id, message).await
+        }
+        async fn send_batch(&self, queue_id: &str, messages: Vec<Message>) -> DataResult<Vec<String>> {
+            self.check()?;
+            self.inner.send_batch(queue_id, messages).await
+        }
+        async fn receive_messages(&self, queue_id: &str, max_messages: i32, wait_time_seconds: i32) -> DataResult<Vec<Message>> {
+            self.inner.receive_messages(queue_id, max_messages, wait_time_seconds).await
+        }
+        async fn delete_message(&self, queue_id: &str, message_id: &str) -> DataResult<()> {
+            self.inner.delete_message(queue_id, message_id).await
+        }
+        async fn peek_messages(&self, queue_id: &str, count: i32) -> DataResult<Vec<Message>> {
+            self.inner.peek_messages(queue_id, count).await
+        }
+        async fn change_visibility(&self, queue_id: &str, message_id: &str, delay: chrono::Duration) -> DataResult<()> {
+            self.inner.change_visibility(queue_id, message_id, delay).await
+        }
+        async fn send_scheduled(&self, queue_id: &str, message: Message, deliver_at: DateTime<Utc>) -> DataResult<String> {
+            self.check()?;
+            self.inner.send_scheduled(queue_id, message, deliver_at).await
+        }
+        async fn list_scheduled(&self, queue_id: &str) -> DataResult<Vec<Message>> {
+            self.inner.list_scheduled(queue_id).await
+        }
+    }
+
+    fn message(id: &str, attributes: &[(&str, &str)]) -> Message {
+        Message {
+            id: id.to_string(),
+            queue_id: String::new(),
+            data: id.as_bytes().to_vec(),
+            attributes: attributes.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            publish_time: Utc::now(),
+            delivery_count: 0,
+            scheduled_for: None,
+            correlation_id: None,
+            reply_to: None,
+        }
+    }
+
+    fn replication(filter: Option<&str>) -> QueueReplication {
+        QueueReplication {
+            id: "orders-dr".to_string(),
+            source_queue: "orders".to_string(),
+            target_queue: "orders-replica".to_string(),
+            target_engine: QueueEngine::RabbitMQ,
+            filter_expression: filter.map(str::to_string),
+            lag_alarm_threshold_seconds: 60,
+            max_buffered_messages: 4,
+        }
+    }
+
+    async fn drain_ids(queue: &dyn MessageOperations, queue_id: &str) -> Vec<String> {
+        let mut ids = Vec::new();
+        loop {
+            let batch = queue.receive_messages(queue_id, 10, 0).await.unwrap();
+            if batch.is_empty() {
+                return ids;
+            }
+            for message in batch {
+                queue.delete_message(queue_id, &message.id).await.unwrap();
+                ids.push(message.id);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_no_loss_or_duplication_across_target_outage() {
+        let source = Arc::new(InMemoryQueueBackend::new());
+        let target = Arc::new(FlakyTarget {
+            inner: InMemoryQueueBackend::new(),
+            down: AtomicBool::new(false),
+        });
+        let checkpoints = Arc::new(InMemoryCheckpointStore::new());
+        let worker = ReplicationWorker::new(
+            replication(None),
+            source.clone(),
+            target.clone(),
+            checkpoints.clone(),
+        )
+        .unwrap();
+
+        for i in 0..3 {
+            source.send_message("orders", message(&format!("m{}", i), &[("region", "us-east-1")])).await.unwrap();
+        }
+        assert_eq!(worker.step().await.unwrap(), 3);
+
+        target.down.store(true, Ordering::SeqCst);
+        for i in 3..10 {
+            source.send_message("orders", message(&format!("m{}", i), &[])).await.unwrap();
+        }
+        assert!(worker.step().await.is_err());
+        assert!(worker.step().await.is_err());
+        let status = worker.status().await;
+        assert!(!status.target_healthy);
+        assert_eq!(status.buffered, 4);
+        assert_eq!(status.position, 3);
+
+        // A restarted worker resumes from the checkpoint rather than replaying from the start
+        drop(worker);
+        target.down.store(false, Ordering::SeqCst);
+        let worker = ReplicationWorker::new(replication(None), source.clone(), target.clone(), checkpoints)
+            .unwrap()
+            .with_batch_size(2);
+        while worker.step().await.unwrap() > 0 {}
+        let status = worker.status().await;
+        assert!(status.target_healthy);
+        assert_eq!(status.position, 10);
+        assert_eq!(status.replicated, 7);
+
+        // Regular consumers on the source still see every message
+        assert_eq!(drain_ids(source.as_ref(), "orders").await.len(), 10);
+
+        let replicated = target.inner.peek_messages("orders-replica", 20).await.unwrap();
+        let ids: Vec<_> = replicated.iter().map(|m| m.id.clone()).collect();
+        let expected: Vec<_> = (0..10).map(|i| format!("m{}", i)).collect();
+        assert_eq!(ids, expected);
+        assert_eq!(replicated[0].attributes["region"], "us-east-1");
+        assert_eq!(replicated[0].attributes[REPLICATED_FROM_ATTRIBUTE], "orders");
+    }
+
+    #[tokio::test]
+    async fn test_loop_marker_and_filter() {
+        let east = Arc::new(InMemoryQueueBackend::new());
+        let west = Arc::new(InMemoryQueueBackend::new());
+        let checkpoints = Arc::new(InMemoryCheckpointStore::new());
+        let mut forward = replication(Some("type = 'order' AND priority != 'low'"));
+        forward.target_queue = "orders".to_string();
+        let mut backward = forward.clone();
+        backward.id = "orders-dr-back".to_string();
+        let forward = ReplicationWorker::new(forward, east.clone(), west.clone(), checkpoints.clone()).unwrap();
+        let backward = ReplicationWorker::new(backward, west.clone(), east.clone(), checkpoints).unwrap();
+
+        east.send_message("orders", message("a", &[("type", "order")])).await.unwrap();
+        east.send_message("orders", message("b", &[("type", "order"), ("priority", "low")])).await.unwrap();
+        east.send_message("orders", message("c", &[("type", "refund")])).await.unwrap();
+
+        assert_eq!(forward.step().await.unwrap(), 1);
+        // The replicated copy carries the marker, so it is not sent back east
+        assert_eq!(backward.step().await.unwrap(), 0);
+        assert_eq!(forward.step().await.unwrap(), 0);
+
+        assert_eq!(drain_ids(west.as_ref(), "orders").await, vec!["a".to_string()]);
+        assert_eq!(drain_ids(east.as_ref(), "orders").await.len(), 3);
+        assert_eq!(forward.status().await.skipped, 2);
+        assert_eq!(backward.status().await.skipped, 1);
+
+        assert!(MessageFilter::parse("type = order").is_err());
+        assert!(MessageFilter::parse("= 'x'").is_err());
+    }
+}
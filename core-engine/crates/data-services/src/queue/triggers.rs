@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use sirsi_common::Retryable;
+use sirsi_compute_manager::error::{ComputeError, ComputeResult};
+use sirsi_compute_manager::serverless::{EventQueue, QueuedMessage};
+
+use crate::error::DataError;
+use super::{Message, MessageOperations};
+
+fn compute_error(error: DataError) -> ComputeError {
+    ComputeError::from_kind(error.kind(), error.to_string())
+}
+
+// Lets compute-manager's event source mappings poll data-services queues
+pub struct QueueEventSource {
+    queues: Arc<dyn MessageOperations>,
+}
+
+impl QueueEventSource {
+    pub fn new(queues: Arc<dyn MessageOperations>) -> Self {
+        Self { queues }
+    }
+}
+
+#[async_trait]
+impl EventQueue for QueueEventSource {
+    async fn receive(&self, queue_id: &str, max_messages: usize) -> ComputeResult<Vec<QueuedMessage>> {
+        // No long polling; the mapping poller has its own interval
+        let messages = self
+            .queues
+            .receive_messages(queue_id, max_messages.min(i32::MAX as usize) as i32, 0)
+            .await
+            .map_err(compute_error)?;
+        Ok(messages
+            .into_iter()
+            .map(|m| QueuedMessage {
+                id: m.id,
+                data: m.data,
+                attributes: m.attributes,
+                published_at: m.publish_time,
+                delivery_count: m.delivery_count.max(1) as u32,
+            })
+            .collect())
+    }
+
+    async fn ack(&self, queue_id: &str, message_id: &str) -> ComputeResult<()> {
+        self.queues.delete_message(queue_id, message_id).await.map_err(compute_error)
+    }
+
+    async fn retry_after(&self, queue_id: &str, message_id: &str, delay: Duration) -> ComputeResult<()> {
+        let delay = chrono::Duration::from_std(delay)
+            .map_err(|e| ComputeError::Validation(format!("Retry delay {:?} is out of range: {}", delay, e)))?;
+        self.queues.change_visibility(queue_id, message_id, delay).await.map_err(compute_error)
+    }
+
+    async fn send(&self, queue_id: &str, data: Vec<u8>, attributes: HashMap<String, String>) -> ComputeResult<()> {
+        let message = Message {
+            id: String::new(),
+            queue_id: queue_id.to_string(),
+            data,
+            attributes,
+            publish_time: Utc::now(),
+            delivery_count: 0,
+            scheduled_for: None,
+            correlation_id: None,
+            reply_to: None,
+        };
+        self.queues.send_message(queue_id, message).await.map(|_| ()).map_err(compute_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::queue::InMemoryQueueBackend;
+    use serde_json::{json, Value};
+    use sirsi_compute_manager::serverless::mapping::DEAD_LETTER_REASON_ATTRIBUTE;
+    use sirsi_compute_manager::serverless::{
+        ErrorPolicy, EventEnvelope, EventSourceMappings, LocalFunctionRuntime, MappingConfig, QueueRef,
+    };
+    use std::sync::Mutex;
+
+    async fn send(backend: &InMemoryQueueBackend, queue: &str, data: Vec<u8>) {
+        let message = Message {
+            id: String::new(),
+            queue_id: queue.into(),
+            data,
+            attributes: HashMap::from([("tenant".to_string(), "acme".to_string())]),
+            publish_time: Utc::now(),
+            delivery_count: 0,
+            scheduled_for: None,
+            correlation_id: None,
+            reply_to: None,
+        };
+        backend.send_message(queue, message).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_queue_mapping_batches_retries_and_dead_letters() {
+        let backend = Arc::new(InMemoryQueueBackend::new());
+        for i in 0..4 {
+            send(&backend, "orders", format!("order-{}", i).into_bytes()).await;
+        }
+        send(&backend, "orders", vec![0xff, 0xfe]).await;
+        send(&backend, "payments", b"charge-1".to_vec()).await;
+
+        let envelopes = Arc::new(Mutex::new(Vec::new()));
+        let seen = envelopes.clone();
+        let runtime = LocalFunctionRuntime::new()
+            .with_handler("ingest", move |payload: Vec<u8>| {
+                seen.lock().unwrap().push(serde_json::from_slice::<Value>(&payload).unwrap());
+                async { Ok(Vec::new()) }
+            })
+            .with_handler("charge", |_| async { Err(ComputeError::Provider("card declined".into())) });
+        let mappings = EventSourceMappings::new(Arc::new(QueueEventSource::new(backend.clone())), Arc::new(runtime));
+
+        let orders = mappings.create_mapping(MappingConfig::queue("ingest", QueueRef::new("orders")).with_batch_size(2)).await.unwrap();
+        let policy = ErrorPolicy {
+            max_retries: 1,
+            retry_delay_seconds: 0,
+            max_retry_delay_seconds: 0,
+            dead_letter_queue: Some(QueueRef::new("payments-dlq")),
+        };
+        let payments = mappings
+            .create_mapping(MappingConfig::queue("charge", QueueRef::new("payments")).with_batch_size(1).with_error_policy(policy))
+            .await
+            .unwrap();
+
+        // Two batches of two side by side, then the last message; the failed charge is retried once
+        assert_eq!(mappings.poll(Utc::now()).await.unwrap(), 3);
+        assert_eq!(mappings.poll(Utc::now()).await.unwrap(), 2);
+        assert_eq!(mappings.poll(Utc::now()).await.unwrap(), 0);
+
+        let envelopes = envelopes.lock().unwrap().clone();
+        let sizes: Vec<usize> = envelopes.iter().map(|e| e["records"].as_array().unwrap().len()).collect();
+        assert_eq!(sizes, vec![2, 2, 1]);
+        let first = &envelopes[0];
+        assert_eq!((first["version"].clone(), first["mapping_id"].clone()), (json!("1"), json!(orders.id)));
+        let record = &first["records"][0];
+        assert_eq!(record["type"], "message");
+        assert_eq!(record["queue_id"], "orders");
+        assert_eq!(record["body"], "order-0");
+        assert_eq!(record["attributes"], json!({"tenant": "acme"}));
+        assert_eq!(record["attempt"], 1);
+        assert_eq!(envelopes[2]["records"][0]["body_base64"], "//4=");
+        assert!(envelopes[2]["records"][0].get("body").is_none());
+        serde_json::from_value::<EventEnvelope>(first.clone()).unwrap();
+        assert!(backend.peek_messages("orders", 10).await.unwrap().is_empty());
+
+        assert!(backend.peek_messages("payments", 10).await.unwrap().is_empty());
+        let dead = backend.receive_messages("payments-dlq", 10, 0).await.unwrap();
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0].data, b"charge-1");
+        assert!(dead[0].attributes[DEAD_LETTER_REASON_ATTRIBUTE].contains("card declined"));
+        let payments = mappings.get_mapping(&payments.id).await.unwrap();
+        assert_eq!((payments.invocations, payments.failures, payments.dead_lettered), (2, 2, 1));
+        assert!(payments.last_error.unwrap().contains("card declined"));
+        assert_eq!(mappings.get_mapping(&orders.id).await.unwrap().failures, 0);
+    }
+}
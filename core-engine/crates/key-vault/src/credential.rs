@@ -0,0 +1,256 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use chrono::Utc;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use zeroize::Zeroizing;
+
+use crate::error::{KeyVaultError, KeyVaultResult};
+use crate::secret::{Secret, SecretManager, SecretValue};
+
+const VAULT_SCHEME: &str = "vault:";
+const MIGRATED_METADATA: &str = "migrated_from";
+
+// Secret material held in memory only: wiped on drop, and deliberately neither Debug,
+// Display nor Serialize so it can't end up in logs or config files by accident
+#[derive(Clone)]
+pub struct SecretString(Zeroizing<String>);
+
+impl SecretString {
+    pub fn new(value: impl Into<String>) -> Self {
+        Self(Zeroizing::new(value.into()))
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+// A key-vault secret, optionally narrowed to one field of a JSON object value.
+// Written as `vault:<secret id>` or `vault:<secret id>#<field>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecretRef {
+    pub secret_id: String,
+    pub field: Option<String>,
+}
+
+impl SecretRef {
+    pub fn new(secret_id: impl Into<String>) -> Self {
+        Self { secret_id: secret_id.into(), field: None }
+    }
+
+    pub fn with_field(mut self, field: impl Into<String>) -> Self {
+        self.field = Some(field.into());
+        self
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        let reference = value.strip_prefix(VAULT_SCHEME)?;
+        let (secret_id, field) = match reference.split_once('#') {
+            Some((secret_id, field)) => (secret_id, Some(field.to_string())),
+            None => (reference, None),
+        };
+        (!secret_id.is_empty()).then(|| Self { secret_id: secret_id.to_string(), field })
+    }
+}
+
+impl fmt::Display for SecretRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.field {
+            Some(field) => write!(f, "{}{}#{}", VAULT_SCHEME, self.secret_id, field),
+            None => write!(f, "{}{}", VAULT_SCHEME, self.secret_id),
+        }
+    }
+}
+
+// A credential as it appears in config: a vault reference, or a legacy plaintext value that
+// is still accepted on load so it can be migrated
+#[derive(Clone)]
+pub enum Credential {
+    Vault(SecretRef),
+    Plaintext(SecretString),
+}
+
+impl fmt::Debug for Credential {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Credential::Vault(reference) => f.debug_tuple("Vault").field(&reference.to_string()).finish(),
+            Credential::Plaintext(_) => f.write_str("Plaintext(<redacted>)"),
+        }
+    }
+}
+
+// Only references are ever written out; a plaintext value fails serialization outright
+// rather than being copied into whatever the config is being saved to
+impl Serialize for Credential {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Credential::Vault(reference) => serializer.serialize_str(&reference.to_string()),
+            Credential::Plaintext(_) => Err(serde::ser::Error::custom(
+                "plaintext credentials must be moved into key-vault before they are written out",
+            )),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Credential {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = Zeroizing::new(String::deserialize(deserializer)?);
+        Ok(match SecretRef::parse(&value) {
+            Some(reference) => Credential::Vault(reference),
+            None => Credential::Plaintext(SecretString::new(value.as_str())),
+        })
+    }
+}
+
+impl Credential {
+    pub fn vault(secret_id: impl Into<String>) -> Self {
+        Credential::Vault(SecretRef::new(secret_id))
+    }
+
+    pub fn plaintext(value: impl Into<String>) -> Self {
+        Credential::Plaintext(SecretString::new(value))
+    }
+
+    pub fn reference(&self) -> Option<&SecretRef> {
+        match self {
+            Credential::Vault(reference) => Some(reference),
+            Credential::Plaintext(_) => None,
+        }
+    }
+
+    pub fn is_plaintext(&self) -> bool {
+        matches!(self, Credential::Plaintext(_))
+    }
+
+    // References are looked up on every call, so a rotated secret is picked up the next time
+    // a client is built
+    pub async fn resolve(&self, secrets: Option<&dyn SecretManager>) -> KeyVaultResult<SecretString> {
+        let reference = match self {
+            Credential::Plaintext(value) => return Ok(value.clone()),
+            Credential::Vault(reference) => reference,
+        };
+        let secrets = secrets.ok_or_else(|| {
+            KeyVaultError::Config(format!("Credential {} needs a secret manager to resolve", reference))
+        })?;
+        let secret = secrets.get_secret(&reference.secret_id).await?;
+        let SecretValue::Plain(value) = &secret.value else {
+            return Err(KeyVaultError::Secret(format!("Secret {} does not hold a text value", reference.secret_id)));
+        };
+        let Some(field) = &reference.field else {
+            return Ok(SecretString::new(value.as_str()));
+        };
+        let fields: serde_json::Value = serde_json::from_str(value).map_err(|_| {
+            KeyVaultError::Secret(format!("Secret {} is not a JSON object", reference.secret_id))
+        })?;
+        fields
+            .get(field)
+            .and_then(|v| v.as_str())
+            .map(SecretString::new)
+            .ok_or_else(|| KeyVaultError::Secret(format!("Secret {} has no field {}", reference.secret_id, field)))
+    }
+
+    // Moves a plaintext value into the vault as `secret_id` and swaps it for a reference.
+    // Returns whether anything changed; re-running over an already migrated value is a no-op.
+    pub async fn ingest(&mut self, secrets: &dyn SecretManager, secret_id: &str) -> KeyVaultResult<bool> {
+        let Credential::Plaintext(value) = &*self else {
+            return Ok(false);
+        };
+        let now = Utc::now();
+        let secret = Secret {
+            id: secret_id.to_string(),
+            name: secret_id.to_string(),
+            description: Some("Migrated from a plaintext config value".into()),
+            value: SecretValue::Plain(value.expose().to_string()),
+            version: 0,
+            created_at: now,
+            updated_at: now,
+            expires_at: None,
+            metadata: HashMap::from([(MIGRATED_METADATA.to_string(), "plaintext".to_string())]),
+            labels: HashMap::new(),
+            rotation_policy: None,
+        };
+        match secrets.create_secret(secret).await {
+            Ok(_) => {}
+            // A config sharing the credential was migrated first; only reuse it if it matches
+            Err(KeyVaultError::Conflict(_)) => {
+                let existing = secrets.get_secret(secret_id).await?;
+                let same = matches!(&existing.value, SecretValue::Plain(v) if v == value.expose());
+                if !same {
+                    return Err(KeyVaultError::Conflict(format!(
+                        "Secret {} already exists with a different value",
+                        secret_id
+                    )));
+                }
+            }
+            Err(e) => return Err(e),
+        }
+        *self = Credential::vault(secret_id);
+        Ok(true)
+    }
+}
+
+// `ingest` for optional config fields, naming the secret `<prefix>/<field>`
+pub async fn ingest_field(
+    credential: &mut Option<Credential>,
+    secrets: &dyn SecretManager,
+    prefix: &str,
+    field: &str,
+) -> KeyVaultResult<bool> {
+    match credential {
+        Some(credential) => credential.ingest(secrets, &format!("{}/{}", prefix, field)).await,
+        None => Ok(false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secret::InMemorySecretManager;
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Config {
+        region: String,
+        secret_key: Credential,
+    }
+
+    #[tokio::test]
+    async fn test_plaintext_is_never_written_back_out() {
+        let secrets = InMemorySecretManager::new();
+        let mut config: Config =
+            serde_json::from_str(r#"{"region":"us-east-1","secret_key":"hunter2-very-secret"}"#).unwrap();
+        assert!(config.secret_key.is_plaintext());
+        assert!(!format!("{:?}", config).contains("hunter2"));
+        assert!(serde_json::to_string(&config).is_err());
+
+        assert!(config.secret_key.ingest(&secrets, "aws/prod/secret_key").await.unwrap());
+        assert!(!config.secret_key.ingest(&secrets, "aws/prod/secret_key").await.unwrap());
+        let written = serde_json::to_string(&config).unwrap();
+        assert_eq!(written, r#"{"region":"us-east-1","secret_key":"vault:aws/prod/secret_key"}"#);
+        assert!(!written.contains("hunter2") && !format!("{:?}", config).contains("hunter2"));
+
+        let reloaded: Config = serde_json::from_str(&written).unwrap();
+        let value = reloaded.secret_key.resolve(Some(&secrets)).await.unwrap();
+        assert_eq!(value.expose(), "hunter2-very-secret");
+        assert!(reloaded.secret_key.resolve(None).await.is_err());
+
+        // Another config holding a different value under the same name is refused
+        let mut other = Credential::plaintext("something-else");
+        assert!(matches!(other.ingest(&secrets, "aws/prod/secret_key").await, Err(KeyVaultError::Conflict(_))));
+    }
+
+    #[tokio::test]
+    async fn test_rotation_and_json_fields() {
+        let secrets = InMemorySecretManager::new();
+        let mut credential = Credential::plaintext(r#"{"client_secret":"first"}"#);
+        credential.ingest(&secrets, "azure/prod").await.unwrap();
+        let field = Credential::Vault(SecretRef::new("azure/prod").with_field("client_secret"));
+        assert_eq!(format!("{:?}", field), r#"Vault("vault:azure/prod#client_secret")"#);
+        assert_eq!(field.resolve(Some(&secrets)).await.unwrap().expose(), "first");
+
+        let mut rotated = secrets.get_secret("azure/prod").await.unwrap();
+        rotated.value = SecretValue::Plain(r#"{"client_secret":"second"}"#.into());
+        secrets.update_secret(rotated).await.unwrap();
+        assert_eq!(field.resolve(Some(&secrets)).await.unwrap().expose(), "second");
+    }
+}
@@ -0,0 +1,404 @@
+//! Envelope encryption: callers receive a fresh data key per payload, encrypt locally and keep
+//! only the wrapped copy, which the vault unwraps again for authorized principals.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use tracing::info;
+use zeroize::Zeroizing;
+
+use crate::error::{KeyVaultError, KeyVaultResult};
+use crate::secret::{AccessPolicyManager, AuditEvent, AuditLogger, SecretAction};
+
+pub const DATA_KEY_LEN: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EncryptionKeyState {
+    Active,
+    DecryptOnly,
+    Revoked,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncryptionKeyVersionInfo {
+    pub version: u32,
+    pub state: EncryptionKeyState,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncryptionKeyInfo {
+    pub name: String,
+    pub current_version: u32,
+    pub versions: Vec<EncryptionKeyVersionInfo>,
+}
+
+// `wrapped` is nonce || AES-256-GCM(plaintext), bound to the key name and version
+pub struct DataKey {
+    pub key_name: String,
+    pub key_version: u32,
+    pub plaintext: Zeroizing<Vec<u8>>,
+    pub wrapped: Vec<u8>,
+}
+
+impl std::fmt::Debug for DataKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DataKey")
+            .field("key_name", &self.key_name)
+            .field("key_version", &self.key_version)
+            .field("plaintext", &"<redacted>")
+            .field("wrapped", &self.wrapped.len())
+            .finish()
+    }
+}
+
+struct KeyVersion {
+    info: EncryptionKeyVersionInfo,
+    material: Zeroizing<Vec<u8>>,
+}
+
+struct EncryptionKey {
+    versions: Vec<KeyVersion>,
+}
+
+impl EncryptionKey {
+    fn current(&self) -> &KeyVersion {
+        self.versions.last().expect("encryption keys always have a version")
+    }
+
+    fn info(&self, name: &str) -> EncryptionKeyInfo {
+        EncryptionKeyInfo {
+            name: name.to_string(),
+            current_version: self.current().info.version,
+            versions: self.versions.iter().map(|v| v.info.clone()).collect(),
+        }
+    }
+}
+
+fn resource_id(name: &str) -> String {
+    format!("crypto/{}", name)
+}
+
+fn aad(name: &str, version: u32) -> Vec<u8> {
+    format!("{}:{}", name, version).into_bytes()
+}
+
+fn random_bytes(rng: &SystemRandom, len: usize) -> KeyVaultResult<Zeroizing<Vec<u8>>> {
+    let mut bytes = Zeroizing::new(vec![0u8; len]);
+    rng.fill(&mut bytes)
+        .map_err(|_| KeyVaultError::Key("System random source failed".into()))?;
+    Ok(bytes)
+}
+
+fn cipher(material: &[u8]) -> KeyVaultResult<LessSafeKey> {
+    let unbound = UnboundKey::new(&AES_256_GCM, material).map_err(|_| KeyVaultError::Internal("Invalid key material".into()))?;
+    Ok(LessSafeKey::new(unbound))
+}
+
+fn new_version(rng: &SystemRandom, version: u32) -> KeyVaultResult<KeyVersion> {
+    Ok(KeyVersion {
+        info: EncryptionKeyVersionInfo {
+            version,
+            state: EncryptionKeyState::Active,
+            created_at: Utc::now(),
+        },
+        material: random_bytes(rng, DATA_KEY_LEN)?,
+    })
+}
+
+pub struct CryptoService {
+    keys: RwLock<HashMap<String, EncryptionKey>>,
+    policies: Arc<dyn AccessPolicyManager>,
+    audit: Arc<dyn AuditLogger>,
+    rng: SystemRandom,
+}
+
+impl CryptoService {
+    pub fn new(policies: Arc<dyn AccessPolicyManager>, audit: Arc<dyn AuditLogger>) -> Self {
+        Self {
+            keys: RwLock::new(HashMap::new()),
+            policies,
+            audit,
+            rng: SystemRandom::new(),
+        }
+    }
+
+    async fn authorize(&self, principal: &str, name: &str, action: SecretAction) -> KeyVaultResult<()> {
+        if self.policies.validate_access(principal, &resource_id(name), action).await? {
+            Ok(())
+        } else {
+            Err(KeyVaultError::Permission(format!(
+                "{} may not {:?} with encryption key {}",
+                principal, action, name
+            )))
+        }
+    }
+
+    // Key use fails closed like signing: nothing is released until the audit record is stored
+    async fn audited<T>(
+        &self,
+        principal: &str,
+        name: &str,
+        action: SecretAction,
+        version: Option<u32>,
+        result: KeyVaultResult<T>,
+    ) -> KeyVaultResult<T> {
+        let mut metadata = HashMap::new();
+        if let Some(version) = version {
+            metadata.insert("key_version".to_string(), version.to_string());
+        }
+        self.audit
+            .log_event(AuditEvent {
+                id: uuid::Uuid::new_v4().to_string(),
+                timestamp: Utc::now(),
+                principal: principal.to_string(),
+                action,
+                secret_id: resource_id(name),
+                success: result.is_ok(),
+                error: result.as_ref().err().map(|e| e.to_string()),
+                metadata,
+            })
+            .await?;
+        result
+    }
+
+    pub async fn create_key(&self, principal: &str, name: &str) -> KeyVaultResult<EncryptionKeyInfo> {
+        self.authorize(principal, name, SecretAction::Write).await?;
+        let mut keys = self.keys.write().await;
+        if keys.contains_key(name) {
+            return Err(KeyVaultError::Conflict(format!("Encryption key {} already exists", name)));
+        }
+        let key = EncryptionKey {
+            versions: vec![new_version(&self.rng, 1)?],
+        };
+        let info = key.info(name);
+        keys.insert(name.to_string(), key);
+        info!("Created encryption key {}", name);
+        Ok(info)
+    }
+
+    pub async fn get_key(&self, principal: &str, name: &str) -> KeyVaultResult<EncryptionKeyInfo> {
+        self.authorize(principal, name, SecretAction::Read).await?;
+        self.keys
+            .read()
+            .await
+            .get(name)
+            .map(|key| key.info(name))
+            .ok_or_else(|| KeyVaultError::NotFound(format!("Encryption key {} not found", name)))
+    }
+
+    // New data keys are wrapped by the new version; keys wrapped earlier still unwrap
+    pub async fn rotate_key(&self, principal: &str, name: &str) -> KeyVaultResult<EncryptionKeyInfo> {
+        self.authorize(principal, name, SecretAction::Rotate).await?;
+        let mut keys = self.keys.write().await;
+        let key = keys
+            .get_mut(name)
+            .ok_or_else(|| KeyVaultError::NotFound(format!("Encryption key {} not found", name)))?;
+        let next = new_version(&self.rng, key.current().info.version + 1)?;
+        for version in key.versions.iter_mut() {
+            if version.info.state == EncryptionKeyState::Active {
+                version.info.state = EncryptionKeyState::DecryptOnly;
+            }
+        }
+        key.versions.push(next);
+        info!("Rotated encryption key {} to version {}", name, key.current().info.version);
+        Ok(key.info(name))
+    }
+
+    pub async fn revoke_version(&self, principal: &str, name: &str, version: u32) -> KeyVaultResult<EncryptionKeyInfo> {
+        self.authorize(principal, name, SecretAction::Rotate).await?;
+        let mut keys = self.keys.write().await;
+        let key = keys
+            .get_mut(name)
+            .ok_or_else(|| KeyVaultError::NotFound(format!("Encryption key {} not found", name)))?;
+        if key.current().info.version == version {
+            return Err(KeyVaultError::Validation(format!(
+                "Version {} is the active version of {}; rotate before revoking it",
+                version, name
+            )));
+        }
+        let target = key
+            .versions
+            .iter_mut()
+            .find(|v| v.info.version == version)
+            .ok_or_else(|| KeyVaultError::NotFound(format!("Encryption key {} has no version {}", name, version)))?;
+        target.info.state = EncryptionKeyState::Revoked;
+        info!("Revoked version {} of encryption key {}", version, name);
+        Ok(key.info(name))
+    }
+
+    pub async fn generate_data_key(&self, principal: &str, name: &str) -> KeyVaultResult<DataKey> {
+        let result = self.generate_inner(principal, name).await;
+        let version = result.as_ref().ok().map(|k| k.key_version);
+        self.audited(principal, name, SecretAction::Encrypt, version, result).await
+    }
+
+    async fn generate_inner(&self, principal: &str, name: &str) -> KeyVaultResult<DataKey> {
+        self.authorize(principal, name, SecretAction::Encrypt).await?;
+        let keys = self.keys.read().await;
+        let key = keys
+            .get(name)
+            .ok_or_else(|| KeyVaultError::NotFound(format!("Encryption key {} not found", name)))?;
+        let current = key.current();
+        let plaintext = random_bytes(&self.rng, DATA_KEY_LEN)?;
+
+        let mut nonce = [0u8; NONCE_LEN];
+        self.rng
+            .fill(&mut nonce)
+            .map_err(|_| KeyVaultError::Key("System random source failed".into()))?;
+        let mut sealed = plaintext.to_vec();
+        cipher(&current.material)?
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(aad(name, current.info.version)),
+                &mut sealed,
+            )
+            .map_err(|_| KeyVaultError::Key("Failed to wrap data key".into()))?;
+        let mut wrapped = nonce.to_vec();
+        wrapped.extend_from_slice(&sealed);
+
+        Ok(DataKey {
+            key_name: name.to_string(),
+            key_version: current.info.version,
+            plaintext,
+            wrapped,
+        })
+    }
+
+    pub async fn decrypt_data_key(
+        &self,
+        principal: &str,
+        name: &str,
+        version: u32,
+        wrapped: &[u8],
+    ) -> KeyVaultResult<Zeroizing<Vec<u8>>> {
+        let result = self.decrypt_inner(principal, name, version, wrapped).await;
+        self.audited(principal, name, SecretAction::Decrypt, Some(version), result).await
+    }
+
+    async fn decrypt_inner(
+        &self,
+        principal: &str,
+        name: &str,
+        version: u32,
+        wrapped: &[u8],
+    ) -> KeyVaultResult<Zeroizing<Vec<u8>>> {
+        self.authorize(principal, name, SecretAction::Decrypt).await?;
+        let keys = self.keys.read().await;
+        let key = keys
+            .get(name)
+            .ok_or_else(|| KeyVaultError::NotFound(format!("Encryption key {} not found", name)))?;
+        let key_version = key
+            .versions
+            .iter()
+            .find(|v| v.info.version == version)
+            .ok_or_else(|| KeyVaultError::NotFound(format!("Encryption key {} has no version {}", name, version)))?;
+        if key_version.info.state == EncryptionKeyState::Revoked {
+            return Err(KeyVaultError::Key(format!(
+                "Version {} of encryption key {} has been revoked",
+                version, name
+            )));
+        }
+        if wrapped.len() < NONCE_LEN {
+            return Err(KeyVaultError::Validation("Wrapped data key is truncated".into()));
+        }
+        let nonce: [u8; NONCE_LEN] = wrapped[..NONCE_LEN].try_into().expect("slice is nonce length");
+        let mut sealed = Zeroizing::new(wrapped[NONCE_LEN..].to_vec());
+        let plaintext = cipher(&key_version.material)?
+            .open_in_place(Nonce::assume_unique_for_key(nonce), Aad::from(aad(name, version)), &mut sealed)
+            .map_err(|_| KeyVaultError::Key(format!("Wrapped data key does not belong to {} version {}", name, version)))?;
+        Ok(Zeroizing::new(plaintext.to_vec()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secret::{AccessPolicy, AuditFilter, InMemoryAccessPolicyManager, InMemoryAuditLogger, SecretPermission};
+
+    async fn service() -> (CryptoService, Arc<InMemoryAuditLogger>) {
+        let policies = InMemoryAccessPolicyManager::new();
+        policies
+            .create_policy(AccessPolicy {
+                id: "queue-crypto".into(),
+                name: "queue-crypto".into(),
+                description: None,
+                principals: vec!["svc-queues".into()],
+                permissions: vec![SecretPermission {
+                    actions: vec![
+                        SecretAction::Read,
+                        SecretAction::Write,
+                        SecretAction::Rotate,
+                        SecretAction::Encrypt,
+                        SecretAction::Decrypt,
+                    ],
+                    secret_patterns: vec!["crypto/*".into()],
+                }],
+                conditions: None,
+            })
+            .await
+            .unwrap();
+        let audit = Arc::new(InMemoryAuditLogger::new());
+        (CryptoService::new(Arc::new(policies), audit.clone()), audit)
+    }
+
+    #[tokio::test]
+    async fn test_data_keys_unwrap_across_rotation_until_revoked() {
+        let (service, _) = service().await;
+        service.create_key("svc-queues", "orders").await.unwrap();
+        let v1 = service.generate_data_key("svc-queues", "orders").await.unwrap();
+        assert_eq!(v1.key_version, 1);
+        assert_eq!(v1.plaintext.len(), DATA_KEY_LEN);
+        assert!(format!("{:?}", v1).contains("<redacted>"));
+
+        service.rotate_key("svc-queues", "orders").await.unwrap();
+        let v2 = service.generate_data_key("svc-queues", "orders").await.unwrap();
+        assert_eq!(v2.key_version, 2);
+
+        for key in [&v1, &v2] {
+            let plaintext = service
+                .decrypt_data_key("svc-queues", "orders", key.key_version, &key.wrapped)
+                .await
+                .unwrap();
+            assert_eq!(*plaintext, *key.plaintext);
+        }
+        // A wrapped key only opens under the version that wrapped it
+        assert!(matches!(
+            service.decrypt_data_key("svc-queues", "orders", 2, &v1.wrapped).await,
+            Err(KeyVaultError::Key(_))
+        ));
+
+        service.revoke_version("svc-queues", "orders", 1).await.unwrap();
+        assert!(service.decrypt_data_key("svc-queues", "orders", 1, &v1.wrapped).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_unauthorized_unwrap_is_denied_and_audited() {
+        let (service, audit) = service().await;
+        service.create_key("svc-queues", "orders").await.unwrap();
+        let key = service.generate_data_key("svc-queues", "orders").await.unwrap();
+        assert!(matches!(
+            service.decrypt_data_key("intruder", "orders", 1, &key.wrapped).await,
+            Err(KeyVaultError::Permission(_))
+        ));
+
+        let events = audit
+            .get_events(AuditFilter {
+                start_time: None,
+                end_time: None,
+                principal: Some("intruder".into()),
+                action: Some(SecretAction::Decrypt),
+                secret_id: Some("crypto/orders".into()),
+                success: None,
+            })
+            .await
+            .unwrap();
+        assert_eq!(events.len(), 1);
+        assert!(!events[0].success);
+    }
+}
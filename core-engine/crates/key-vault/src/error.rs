@@ -27,6 +27,9 @@ pub enum KeyVaultError {
     #[error("Resource not found: {0}")]
     NotFound(String),
 
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
     #[error("Configuration error: {0}")]
     Config(String),
 
@@ -51,6 +54,7 @@ impl From<KeyVaultError> for Status {
             KeyVaultError::Database(e) => Status::internal(e.to_string()),
             KeyVaultError::Validation(msg) => Status::invalid_argument(msg),
             KeyVaultError::NotFound(msg) => Status::not_found(msg),
+            KeyVaultError::Conflict(msg) => Status::aborted(msg),
             KeyVaultError::Config(msg) => Status::failed_precondition(msg),
             KeyVaultError::Permission(msg) => Status::permission_denied(msg),
             KeyVaultError::Service(msg) => Status::internal(msg),
@@ -5,6 +5,35 @@
 #![forbid(unsafe_code)]
 #![warn(missing_docs)]
 
+// These modules predate the docs lint and are documented incrementally
+/// Credentials referenced by secret id and resolved through a secret manager
+#[allow(missing_docs)]
+pub mod credential;
+/// Envelope encryption: data keys wrapped by versioned vault keys
+#[allow(missing_docs)]
+pub mod crypto;
+/// Key vault error types
+#[allow(missing_docs)]
+pub mod error;
+/// Secret storage, rotation and access policies
+#[allow(missing_docs)]
+pub mod secret;
+/// Transit-style signing keys
+#[allow(missing_docs)]
+pub mod signing;
+/// SSH user certificate authority backed by a signing key
+#[allow(missing_docs)]
+pub mod ssh;
+/// Per-tenant master keys, field encryption and re-encryption after rotation
+#[allow(missing_docs)]
+pub mod tenant;
+/// HashiCorp Vault KV v2 compatible HTTP API
+#[allow(missing_docs)]
+pub mod vault;
+
+pub use credential::{Credential, SecretRef, SecretString};
+pub use error::{KeyVaultError, KeyVaultResult};
+
 /// Returns the current version of the key-vault service
 pub fn version() -> &'static str {
     env!("CARGO_PKG_VERSION")
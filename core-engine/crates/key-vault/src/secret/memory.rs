@@ -0,0 +1,330 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use chrono::{Datelike, Utc};
+use tokio::sync::RwLock;
+
+use crate::error::{KeyVaultError, KeyVaultResult};
+use super::{
+    AccessPolicy, AccessPolicyManager, AuditEvent, AuditFilter, AuditLogger, RotationEvent, Secret, SecretAction,
+    SecretManager,
+};
+
+// Process-local secret store keeping every version, used for tests and local runs
+#[derive(Default)]
+pub struct InMemorySecretManager {
+    secrets: RwLock<HashMap<String, Vec<Secret>>>,
+}
+
+impl InMemorySecretManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+fn not_found(id: &str) -> KeyVaultError {
+    KeyVaultError::NotFound(format!("Secret {} not found", id))
+}
+
+#[async_trait]
+impl SecretManager for InMemorySecretManager {
+    async fn create_secret(&self, mut secret: Secret) -> KeyVaultResult<Secret> {
+        let mut secrets = self.secrets.write().await;
+        if secrets.contains_key(&secret.id) {
+            return Err(KeyVaultError::Conflict(format!("Secret {} already exists", secret.id)));
+        }
+        secret.version = 1;
+        secrets.insert(secret.id.clone(), vec![secret.clone()]);
+        Ok(secret)
+    }
+
+    async fn get_secret(&self, id: &str) -> KeyVaultResult<Secret> {
+        self.secrets
+            .read()
+            .await
+            .get(id)
+            .and_then(|versions| versions.last().cloned())
+            .ok_or_else(|| not_found(id))
+    }
+
+    async fn get_secret_version(&self, id: &str, version: i32) -> KeyVaultResult<Secret> {
+        self.secrets
+            .read()
+            .await
+            .get(id)
+            .ok_or_else(|| not_found(id))?
+            .iter()
+            .find(|s| s.version == version)
+            .cloned()
+            .ok_or_else(|| KeyVaultError::NotFound(format!("Secret {} has no version {}", id, version)))
+    }
+
+    // Every update appends a new version; earlier versions stay readable
+    async fn update_secret(&self, mut secret: Secret) -> KeyVaultResult<Secret> {
+        let mut secrets = self.secrets.write().await;
+        let versions = secrets.get_mut(&secret.id).ok_or_else(|| not_found(&secret.id))?;
+        let current = versions.last().expect("stored secrets always have a version");
+        secret.version = current.version + 1;
+        secret.created_at = versions[0].created_at;
+        secret.updated_at = Utc::now();
+        versions.push(secret.clone());
+        Ok(secret)
+    }
+
+    async fn delete_secret(&self, id: &str) -> KeyVaultResult<()> {
+        self.secrets
+            .write()
+            .await
+            .remove(id)
+            .map(|_| ())
+            .ok_or_else(|| not_found(id))
+    }
+
+    async fn list_secrets(&self) -> KeyVaultResult<Vec<Secret>> {
+        let mut secrets: Vec<Secret> = self
+            .secrets
+            .read()
+            .await
+            .values()
+            .filter_map(|versions| versions.last().cloned())
+            .collect();
+        secrets.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(secrets)
+    }
+
+    async fn rotate_secret(&self, id: &str) -> KeyVaultResult<RotationEvent> {
+        Err(KeyVaultError::Config(format!(
+            "Secret {} cannot be rotated: the in-memory store has no rotation backend",
+            id
+        )))
+    }
+
+    async fn get_rotation_history(&self, id: &str) -> KeyVaultResult<Vec<RotationEvent>> {
+        self.get_secret(id).await.map(|_| Vec::new())
+    }
+}
+
+// Glob match where `*` spans any run of characters, including `/`
+pub(crate) fn pattern_matches(pattern: &str, value: &str) -> bool {
+    let Some((prefix, rest)) = pattern.split_once('*') else {
+        return pattern == value;
+    };
+    let Some(mut remaining) = value.strip_prefix(prefix) else {
+        return false;
+    };
+    let mut parts = rest.split('*').peekable();
+    while let Some(part) = parts.next() {
+        if parts.peek().is_none() {
+            return remaining.ends_with(part);
+        }
+        match remaining.find(part) {
+            Some(index) => remaining = &remaining[index + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+#[derive(Default)]
+pub struct InMemoryAccessPolicyManager {
+    policies: RwLock<HashMap<String, AccessPolicy>>,
+}
+
+impl InMemoryAccessPolicyManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+fn conditions_hold(policy: &AccessPolicy) -> bool {
+    let Some(conditions) = &policy.conditions else {
+        return true;
+    };
+    // Without request context, IP and MFA conditions cannot be proven and so deny
+    if conditions.requires_mfa || conditions.ip_ranges.is_some() {
+        return false;
+    }
+    conditions
+        .time_window
+        .as_ref()
+        .map(|window| {
+            let now = Utc::now();
+            let time = now.time();
+            (window.days.is_empty() || window.days.contains(&now.weekday()))
+                && time >= window.start_time
+                && time <= window.end_time
+        })
+        .unwrap_or(true)
+}
+
+#[async_trait]
+impl AccessPolicyManager for InMemoryAccessPolicyManager {
+    async fn create_policy(&self, policy: AccessPolicy) -> KeyVaultResult<AccessPolicy> {
+        let mut policies = self.policies.write().await;
+        if policies.contains_key(&policy.id) {
+            return Err(KeyVaultError::Conflict(format!("Policy {} already exists", policy.id)));
+        }
+        policies.insert(policy.id.clone(), policy.clone());
+        Ok(policy)
+    }
+
+    async fn get_policy(&self, id: &str) -> KeyVaultResult<AccessPolicy> {
+        self.policies
+            .read()
+            .await
+            .get(id)
+            .cloned()
+            .ok_or_else(|| KeyVaultError::NotFound(format!("Policy {} not found", id)))
+    }
+
+    async fn update_policy(&self, policy: AccessPolicy) -> KeyVaultResult<AccessPolicy> {
+        let mut policies = self.policies.write().await;
+        if !policies.contains_key(&policy.id) {
+            return Err(KeyVaultError::NotFound(format!("Policy {} not found", policy.id)));
+        }
+        policies.insert(policy.id.clone(), policy.clone());
+        Ok(policy)
+    }
+
+    async fn delete_policy(&self, id: &str) -> KeyVaultResult<()> {
+        self.policies
+            .write()
+            .await
+            .remove(id)
+            .map(|_| ())
+            .ok_or_else(|| KeyVaultError::NotFound(format!("Policy {} not found", id)))
+    }
+
+    async fn list_policies(&self) -> KeyVaultResult<Vec<AccessPolicy>> {
+        let mut policies: Vec<AccessPolicy> = self.policies.read().await.values().cloned().collect();
+        policies.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(policies)
+    }
+
+    async fn validate_access(&self, principal: &str, secret_id: &str, action: SecretAction) -> KeyVaultResult<bool> {
+        Ok(self.policies.read().await.values().any(|policy| {
+            policy.principals.iter().any(|p| pattern_matches(p, principal))
+                && conditions_hold(policy)
+                && policy.permissions.iter().any(|permission| {
+                    permission.actions.contains(&action)
+                        && permission.secret_patterns.iter().any(|p| pattern_matches(p, secret_id))
+                })
+        }))
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryAuditLogger {
+    events: RwLock<Vec<AuditEvent>>,
+}
+
+impl InMemoryAuditLogger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+fn event_matches(event: &AuditEvent, filter: &AuditFilter) -> bool {
+    filter.start_time.is_none_or(|start| event.timestamp >= start)
+        && filter.end_time.is_none_or(|end| event.timestamp <= end)
+        && filter.principal.as_ref().is_none_or(|p| &event.principal == p)
+        && filter.action.is_none_or(|a| event.action == a)
+        && filter.secret_id.as_ref().is_none_or(|id| &event.secret_id == id)
+        && filter.success.is_none_or(|s| event.success == s)
+}
+
+#[async_trait]
+impl AuditLogger for InMemoryAuditLogger {
+    async fn log_event(&self, event: AuditEvent) -> KeyVaultResult<()> {
+        self.events.write().await.push(event);
+        Ok(())
+    }
+
+    async fn get_events(&self, filter: AuditFilter) -> KeyVaultResult<Vec<AuditEvent>> {
+        Ok(self
+            .events
+            .read()
+            .await
+            .iter()
+            .filter(|event| event_matches(event, &filter))
+            .cloned()
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secret::SecretValue;
+
+    fn secret(id: &str, value: &str) -> Secret {
+        Secret {
+            id: id.to_string(),
+            name: id.to_string(),
+            description: None,
+            value: SecretValue::Plain(value.to_string()),
+            version: 0,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            expires_at: None,
+            metadata: HashMap::new(),
+            labels: HashMap::new(),
+            rotation_policy: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_versions_accumulate() {
+        let manager = InMemorySecretManager::new();
+        manager.create_secret(secret("db", "one")).await.unwrap();
+        assert!(matches!(
+            manager.create_secret(secret("db", "again")).await,
+            Err(KeyVaultError::Conflict(_))
+        ));
+
+        let updated = manager.update_secret(secret("db", "two")).await.unwrap();
+        assert_eq!(updated.version, 2);
+        assert!(matches!(manager.get_secret("db").await.unwrap().value, SecretValue::Plain(v) if v == "two"));
+        assert!(matches!(
+            manager.get_secret_version("db", 1).await.unwrap().value,
+            SecretValue::Plain(v) if v == "one"
+        ));
+        assert!(manager.get_secret_version("db", 3).await.is_err());
+    }
+
+    #[test]
+    fn test_pattern_matches() {
+        assert!(pattern_matches("*", "kv/app/db"));
+        assert!(pattern_matches("kv/app/*", "kv/app/db/password"));
+        assert!(pattern_matches("kv/*/db", "kv/app/db"));
+        assert!(pattern_matches("kv/app/db", "kv/app/db"));
+        assert!(!pattern_matches("kv/app/*", "kv/other/db"));
+        assert!(!pattern_matches("kv/*/db", "kv/app/cache"));
+    }
+
+    #[tokio::test]
+    async fn test_validate_access() {
+        use crate::secret::SecretPermission;
+
+        let policies = InMemoryAccessPolicyManager::new();
+        policies
+            .create_policy(AccessPolicy {
+                id: "app-read".into(),
+                name: "app-read".into(),
+                description: None,
+                principals: vec!["svc-*".into()],
+                permissions: vec![SecretPermission {
+                    actions: vec![SecretAction::Read, SecretAction::List],
+                    secret_patterns: vec!["kv/app/*".into()],
+                }],
+                conditions: None,
+            })
+            .await
+            .unwrap();
+
+        assert!(policies.validate_access("svc-web", "kv/app/db", SecretAction::Read).await.unwrap());
+        assert!(!policies.validate_access("svc-web", "kv/app/db", SecretAction::Write).await.unwrap());
+        assert!(!policies.validate_access("svc-web", "kv/ops/db", SecretAction::Read).await.unwrap());
+        assert!(!policies.validate_access("alice", "kv/app/db", SecretAction::Read).await.unwrap());
+    }
+}
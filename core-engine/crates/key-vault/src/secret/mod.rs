@@ -5,6 +5,12 @@ use chrono::{DateTime, Utc};
 
 use crate::error::KeyVaultResult;
 
+pub mod archive;
+pub mod memory;
+
+pub use archive::{export_secrets, import_secrets, ConflictPolicy, ImportOutcome, ImportReport, SecretFilter};
+pub use memory::{InMemoryAccessPolicyManager, InMemoryAuditLogger, InMemorySecretManager};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Secret {
     pub id: String,
@@ -105,13 +111,16 @@ pub struct SecretPermission {
     pub secret_patterns: Vec<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum SecretAction {
     Read,
     Write,
     Delete,
     List,
     Rotate,
+    Sign,
+    Encrypt,
+    Decrypt,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
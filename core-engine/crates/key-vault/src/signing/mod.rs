@@ -0,0 +1,514 @@
+//! Transit-style signing: services sign and verify payloads with named keys
+//! whose private material never leaves the vault.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use openssl::ec::{EcGroup, EcKey};
+use openssl::md::Md;
+use openssl::nid::Nid;
+use openssl::pkey::{PKey, Private};
+use openssl::pkey_ctx::PkeyCtx;
+use openssl::rsa::{Padding, Rsa};
+use openssl::sign::{RsaPssSaltlen, Signer, Verifier};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use tracing::info;
+
+use crate::error::{KeyVaultError, KeyVaultResult};
+use crate::secret::{AccessPolicyManager, AuditEvent, AuditLogger, SecretAction};
+
+pub const RSA_KEY_BITS: u32 = 2048;
+pub const SHA256_DIGEST_LEN: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SigningAlgorithm {
+    Ed25519,
+    EcdsaP256,
+    RsaPss,
+}
+
+impl SigningAlgorithm {
+    fn generate(self) -> KeyVaultResult<PKey<Private>> {
+        let key = match self {
+            Self::Ed25519 => PKey::generate_ed25519(),
+            Self::EcdsaP256 => EcGroup::from_curve_name(Nid::X9_62_PRIME256V1)
+                .and_then(|group| EcKey::generate(&group))
+                .and_then(PKey::from_ec_key),
+            Self::RsaPss => Rsa::generate(RSA_KEY_BITS).and_then(PKey::from_rsa),
+        };
+        key.map_err(|e| KeyVaultError::Key(format!("Failed to generate {:?} key: {}", self, e)))
+    }
+}
+
+// Ed25519 only signs raw messages; ECDSA and RSA-PSS also accept a precomputed SHA-256 digest
+#[derive(Debug, Clone)]
+pub enum SigningInput {
+    Raw(Vec<u8>),
+    Sha256Digest(Vec<u8>),
+}
+
+impl SigningInput {
+    fn digest(&self) -> KeyVaultResult<Vec<u8>> {
+        match self {
+            Self::Raw(message) => Ok(openssl::sha::sha256(message).to_vec()),
+            Self::Sha256Digest(digest) if digest.len() == SHA256_DIGEST_LEN => Ok(digest.clone()),
+            Self::Sha256Digest(digest) => Err(KeyVaultError::Validation(format!(
+                "SHA-256 digest must be {} bytes, got {}",
+                SHA256_DIGEST_LEN,
+                digest.len()
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KeyVersionState {
+    Active,
+    VerifyOnly,
+    Revoked,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyVersionInfo {
+    pub version: u32,
+    pub state: KeyVersionState,
+    pub public_key_der: Vec<u8>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SigningKeyInfo {
+    pub name: String,
+    pub algorithm: SigningAlgorithm,
+    pub current_version: u32,
+    pub versions: Vec<KeyVersionInfo>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Signature {
+    pub key_version: u32,
+    pub algorithm: SigningAlgorithm,
+    pub bytes: Vec<u8>,
+}
+
+struct KeyVersion {
+    info: KeyVersionInfo,
+    private_key: PKey<Private>,
+}
+
+struct SigningKey {
+    algorithm: SigningAlgorithm,
+    versions: Vec<KeyVersion>,
+}
+
+impl SigningKey {
+    fn current(&self) -> &KeyVersion {
+        self.versions.last().expect("signing keys always have a version")
+    }
+
+    fn version(&self, name: &str, version: u32) -> KeyVaultResult<&KeyVersion> {
+        self.versions
+            .iter()
+            .find(|v| v.info.version == version)
+            .ok_or_else(|| KeyVaultError::NotFound(format!("Signing key {} has no version {}", name, version)))
+    }
+
+    fn info(&self, name: &str) -> SigningKeyInfo {
+        SigningKeyInfo {
+            name: name.to_string(),
+            algorithm: self.algorithm,
+            current_version: self.current().info.version,
+            versions: self.versions.iter().map(|v| v.info.clone()).collect(),
+        }
+    }
+}
+
+fn new_version(algorithm: SigningAlgorithm, version: u32) -> KeyVaultResult<KeyVersion> {
+    let private_key = algorithm.generate()?;
+    let public_key_der = private_key
+        .public_key_to_der()
+        .map_err(|e| KeyVaultError::Key(format!("Failed to encode public key: {}", e)))?;
+
+    Ok(KeyVersion {
+        info: KeyVersionInfo {
+            version,
+            state: KeyVersionState::Active,
+            public_key_der,
+            created_at: Utc::now(),
+        },
+        private_key,
+    })
+}
+
+fn crypto_error(e: openssl::error::ErrorStack) -> KeyVaultError {
+    KeyVaultError::Key(format!("Signing operation failed: {}", e))
+}
+
+fn sign_with(algorithm: SigningAlgorithm, key: &PKey<Private>, input: &SigningInput) -> KeyVaultResult<Vec<u8>> {
+    match (algorithm, input) {
+        (SigningAlgorithm::Ed25519, SigningInput::Raw(message)) => Signer::new_without_digest(key)
+            .and_then(|mut signer| signer.sign_oneshot_to_vec(message))
+            .map_err(crypto_error),
+        (SigningAlgorithm::Ed25519, SigningInput::Sha256Digest(_)) => Err(KeyVaultError::Validation(
+            "Ed25519 keys sign raw messages only".into(),
+        )),
+        _ => {
+            let digest = input.digest()?;
+            let mut ctx = PkeyCtx::new(key).map_err(crypto_error)?;
+            ctx.sign_init().map_err(crypto_error)?;
+            configure_ctx(algorithm, &mut ctx).map_err(crypto_error)?;
+            let mut signature = Vec::new();
+            ctx.sign_to_vec(&digest, &mut signature).map_err(crypto_error)?;
+            Ok(signature)
+        }
+    }
+}
+
+fn verify_with(
+    algorithm: SigningAlgorithm,
+    key: &PKey<Private>,
+    input: &SigningInput,
+    signature: &[u8],
+) -> KeyVaultResult<bool> {
+    // Malformed signatures surface as OpenSSL errors; both mean "does not verify"
+    match (algorithm, input) {
+        (SigningAlgorithm::Ed25519, SigningInput::Raw(message)) => Ok(Verifier::new_without_digest(key)
+            .and_then(|mut verifier| verifier.verify_oneshot(signature, message))
+            .unwrap_or(false)),
+        (SigningAlgorithm::Ed25519, SigningInput::Sha256Digest(_)) => Err(KeyVaultError::Validation(
+            "Ed25519 keys verify raw messages only".into(),
+        )),
+        _ => {
+            let digest = input.digest()?;
+            let mut ctx = PkeyCtx::new(key).map_err(crypto_error)?;
+            ctx.verify_init().map_err(crypto_error)?;
+            configure_ctx(algorithm, &mut ctx).map_err(crypto_error)?;
+            Ok(ctx.verify(&digest, signature).unwrap_or(false))
+        }
+    }
+}
+
+fn configure_ctx(algorithm: SigningAlgorithm, ctx: &mut PkeyCtx<Private>) -> Result<(), openssl::error::ErrorStack> {
+    ctx.set_signature_md(Md::sha256())?;
+    if algorithm == SigningAlgorithm::RsaPss {
+        ctx.set_rsa_padding(Padding::PKCS1_PSS)?;
+        ctx.set_rsa_pss_saltlen(RsaPssSaltlen::DIGEST_LENGTH)?;
+    }
+    Ok(())
+}
+
+fn resource_id(name: &str) -> String {
+    format!("signing/{}", name)
+}
+
+pub struct SigningService {
+    keys: RwLock<HashMap<String, SigningKey>>,
+    policies: Arc<dyn AccessPolicyManager>,
+    audit: Arc<dyn AuditLogger>,
+}
+
+impl SigningService {
+    pub fn new(policies: Arc<dyn AccessPolicyManager>, audit: Arc<dyn AuditLogger>) -> Self {
+        Self {
+            keys: RwLock::new(HashMap::new()),
+            policies,
+            audit,
+        }
+    }
+
+    async fn authorize(&self, principal: &str, name: &str, action: SecretAction) -> KeyVaultResult<()> {
+        if self.policies.validate_access(principal, &resource_id(name), action).await? {
+            Ok(())
+        } else {
+            Err(KeyVaultError::Permission(format!(
+                "{} may not {:?} signing key {}",
+                principal, action, name
+            )))
+        }
+    }
+
+    pub async fn create_key(
+        &self,
+        principal: &str,
+        name: &str,
+        algorithm: SigningAlgorithm,
+    ) -> KeyVaultResult<SigningKeyInfo> {
+        self.authorize(principal, name, SecretAction::Write).await?;
+        let mut keys = self.keys.write().await;
+        if keys.contains_key(name) {
+            return Err(KeyVaultError::Conflict(format!("Signing key {} already exists", name)));
+        }
+        let key = SigningKey {
+            algorithm,
+            versions: vec![new_version(algorithm, 1)?],
+        };
+        let info = key.info(name);
+        keys.insert(name.to_string(), key);
+        info!("Created {:?} signing key {}", algorithm, name);
+        Ok(info)
+    }
+
+    pub async fn get_key(&self, principal: &str, name: &str) -> KeyVaultResult<SigningKeyInfo> {
+        self.authorize(principal, name, SecretAction::Read).await?;
+        self.keys
+            .read()
+            .await
+            .get(name)
+            .map(|key| key.info(name))
+            .ok_or_else(|| KeyVaultError::NotFound(format!("Signing key {} not found", name)))
+    }
+
+    // The new version signs from now on; earlier versions remain usable for verification only
+    pub async fn rotate_key(&self, principal: &str, name: &str) -> KeyVaultResult<SigningKeyInfo> {
+        self.authorize(principal, name, SecretAction::Rotate).await?;
+        let mut keys = self.keys.write().await;
+        let key = keys
+            .get_mut(name)
+            .ok_or_else(|| KeyVaultError::NotFound(format!("Signing key {} not found", name)))?;
+        let next = new_version(key.algorithm, key.current().info.version + 1)?;
+        for version in key.versions.iter_mut() {
+            if version.info.state == KeyVersionState::Active {
+                version.info.state = KeyVersionState::VerifyOnly;
+            }
+        }
+        key.versions.push(next);
+        info!("Rotated signing key {} to version {}", name, key.current().info.version);
+        Ok(key.info(name))
+    }
+
+    pub async fn revoke_version(&self, principal: &str, name: &str, version: u32) -> KeyVaultResult<SigningKeyInfo> {
+        self.authorize(principal, name, SecretAction::Rotate).await?;
+        let mut keys = self.keys.write().await;
+        let key = keys
+            .get_mut(name)
+            .ok_or_else(|| KeyVaultError::NotFound(format!("Signing key {} not found", name)))?;
+        if key.current().info.version == version {
+            return Err(KeyVaultError::Validation(format!(
+                "Version {} is the active version of {}; rotate before revoking it",
+                version, name
+            )));
+        }
+        let target = key
+            .versions
+            .iter_mut()
+            .find(|v| v.info.version == version)
+            .ok_or_else(|| KeyVaultError::NotFound(format!("Signing key {} has no version {}", name, version)))?;
+        target.info.state = KeyVersionState::Revoked;
+        info!("Revoked version {} of signing key {}", version, name);
+        Ok(key.info(name))
+    }
+
+    pub async fn sign(&self, principal: &str, name: &str, input: SigningInput) -> KeyVaultResult<Signature> {
+        let result = self.sign_inner(principal, name, &input).await;
+
+        let mut metadata = HashMap::new();
+        if let Ok(signature) = &result {
+            metadata.insert("key_version".to_string(), signature.key_version.to_string());
+            metadata.insert("algorithm".to_string(), format!("{:?}", signature.algorithm));
+        }
+        let input_kind = match input {
+            SigningInput::Raw(_) => "raw",
+            SigningInput::Sha256Digest(_) => "sha256_digest",
+        };
+        metadata.insert("input".to_string(), input_kind.to_string());
+
+        // Signing fails closed: a signature is only released once its audit record is stored
+        self.audit
+            .log_event(AuditEvent {
+                id: uuid::Uuid::new_v4().to_string(),
+                timestamp: Utc::now(),
+                principal: principal.to_string(),
+                action: SecretAction::Sign,
+                secret_id: resource_id(name),
+                success: result.is_ok(),
+                error: result.as_ref().err().map(|e| e.to_string()),
+                metadata,
+            })
+            .await?;
+        result
+    }
+
+    async fn sign_inner(&self, principal: &str, name: &str, input: &SigningInput) -> KeyVaultResult<Signature> {
+        self.authorize(principal, name, SecretAction::Sign).await?;
+        let keys = self.keys.read().await;
+        let key = keys
+            .get(name)
+            .ok_or_else(|| KeyVaultError::NotFound(format!("Signing key {} not found", name)))?;
+        let current = key.current();
+        Ok(Signature {
+            key_version: current.info.version,
+            algorithm: key.algorithm,
+            bytes: sign_with(key.algorithm, &current.private_key, input)?,
+        })
+    }
+
+    // Accepts signatures from any non-revoked version; revoked versions are rejected outright
+    pub async fn verify(
+        &self,
+        principal: &str,
+        name: &str,
+        input: SigningInput,
+        signature: &Signature,
+    ) -> KeyVaultResult<bool> {
+        self.authorize(principal, name, SecretAction::Read).await?;
+        let keys = self.keys.read().await;
+        let key = keys
+            .get(name)
+            .ok_or_else(|| KeyVaultError::NotFound(format!("Signing key {} not found", name)))?;
+        if signature.algorithm != key.algorithm {
+            return Ok(false);
+        }
+        let version = key.version(name, signature.key_version)?;
+        if version.info.state == KeyVersionState::Revoked {
+            return Err(KeyVaultError::Key(format!(
+                "Version {} of signing key {} has been revoked",
+                signature.key_version, name
+            )));
+        }
+        verify_with(key.algorithm, &version.private_key, &input, &signature.bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secret::{AccessPolicy, AuditFilter, InMemoryAccessPolicyManager, InMemoryAuditLogger, SecretPermission};
+
+    async fn service() -> (SigningService, Arc<InMemoryAuditLogger>) {
+        let policies = InMemoryAccessPolicyManager::new();
+        policies
+            .create_policy(AccessPolicy {
+                id: "signer".into(),
+                name: "signer".into(),
+                description: None,
+                principals: vec!["svc-release".into()],
+                permissions: vec![SecretPermission {
+                    actions: vec![
+                        SecretAction::Read,
+                        SecretAction::Write,
+                        SecretAction::Rotate,
+                        SecretAction::Sign,
+                    ],
+                    secret_patterns: vec!["signing/*".into()],
+                }],
+                conditions: None,
+            })
+            .await
+            .unwrap();
+        let audit = Arc::new(InMemoryAuditLogger::new());
+        (SigningService::new(Arc::new(policies), audit.clone()), audit)
+    }
+
+    #[tokio::test]
+    async fn test_sign_and_verify_across_versions() {
+        let (service, _) = service().await;
+        let message = b"release-artifact".to_vec();
+
+        for (name, algorithm) in [
+            ("ed", SigningAlgorithm::Ed25519),
+            ("ec", SigningAlgorithm::EcdsaP256),
+            ("rsa", SigningAlgorithm::RsaPss),
+        ] {
+            service.create_key("svc-release", name, algorithm).await.unwrap();
+            let v1 = service
+                .sign("svc-release", name, SigningInput::Raw(message.clone()))
+                .await
+                .unwrap();
+            assert_eq!(v1.key_version, 1);
+
+            let rotated = service.rotate_key("svc-release", name).await.unwrap();
+            assert_eq!(rotated.current_version, 2);
+            assert_eq!(rotated.versions[0].state, KeyVersionState::VerifyOnly);
+
+            let v2 = service
+                .sign("svc-release", name, SigningInput::Raw(message.clone()))
+                .await
+                .unwrap();
+            assert_eq!(v2.key_version, 2);
+
+            for signature in [&v1, &v2] {
+                assert!(service
+                    .verify("svc-release", name, SigningInput::Raw(message.clone()), signature)
+                    .await
+                    .unwrap());
+            }
+            assert!(!service
+                .verify("svc-release", name, SigningInput::Raw(b"tampered".to_vec()), &v1)
+                .await
+                .unwrap());
+        }
+
+        // Digest and raw inputs produce interchangeable signatures
+        let digest = openssl::sha::sha256(&message).to_vec();
+        let signature = service
+            .sign("svc-release", "ec", SigningInput::Sha256Digest(digest))
+            .await
+            .unwrap();
+        assert!(service
+            .verify("svc-release", "ec", SigningInput::Raw(message.clone()), &signature)
+            .await
+            .unwrap());
+        assert!(service
+            .sign("svc-release", "ed", SigningInput::Sha256Digest(vec![0; 32]))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_revoked_versions_are_rejected() {
+        let (service, _) = service().await;
+        service
+            .create_key("svc-release", "ed", SigningAlgorithm::Ed25519)
+            .await
+            .unwrap();
+        let message = SigningInput::Raw(b"payload".to_vec());
+        let old = service.sign("svc-release", "ed", message.clone()).await.unwrap();
+
+        assert!(service.revoke_version("svc-release", "ed", 1).await.is_err());
+        service.rotate_key("svc-release", "ed").await.unwrap();
+        service.revoke_version("svc-release", "ed", 1).await.unwrap();
+
+        assert!(matches!(
+            service.verify("svc-release", "ed", message.clone(), &old).await,
+            Err(KeyVaultError::Key(_))
+        ));
+        let fresh = service.sign("svc-release", "ed", message.clone()).await.unwrap();
+        assert!(service.verify("svc-release", "ed", message, &fresh).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_sign_calls_are_audited() {
+        let (service, audit) = service().await;
+        service
+            .create_key("svc-release", "ec", SigningAlgorithm::EcdsaP256)
+            .await
+            .unwrap();
+        service
+            .sign("svc-release", "ec", SigningInput::Raw(b"ok".to_vec()))
+            .await
+            .unwrap();
+        assert!(matches!(
+            service.sign("intruder", "ec", SigningInput::Raw(b"nope".to_vec())).await,
+            Err(KeyVaultError::Permission(_))
+        ));
+
+        let events = audit
+            .get_events(AuditFilter {
+                start_time: None,
+                end_time: None,
+                principal: None,
+                action: Some(SecretAction::Sign),
+                secret_id: Some("signing/ec".into()),
+                success: None,
+            })
+            .await
+            .unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(events[0].success);
+        assert_eq!(events[0].metadata["key_version"], "1");
+        assert!(!events[1].success);
+        assert_eq!(events[1].principal, "intruder");
+    }
+}
@@ -0,0 +1,558 @@
+//! SSH user certificate authority. Operators present their own public key and get back a
+//! certificate valid for minutes, signed by an Ed25519 key held in the signing service; instances
+//! trust the CA's public keys through `TrustedUserCAKeys` instead of carrying per-user keys.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::{DateTime, Duration, Utc};
+use openssl::pkey::{Id, PKey};
+use openssl::sign::Verifier;
+use serde::Serialize;
+use tracing::info;
+
+use crate::error::{KeyVaultError, KeyVaultResult};
+use crate::signing::{KeyVersionState, SigningAlgorithm, SigningInput, SigningService};
+
+mod wire;
+
+use wire::{Reader, Writer};
+
+pub const DEFAULT_CA_KEY: &str = "ssh-user-ca";
+pub const MAX_CERTIFICATE_TTL_MINUTES: i64 = 60;
+pub const FORCE_COMMAND: &str = "force-command";
+pub const SOURCE_ADDRESS: &str = "source-address";
+pub const PERMIT_PTY: &str = "permit-pty";
+
+// The extensions OpenSSH understands; anything else would be silently ignored by sshd
+pub const KNOWN_EXTENSIONS: &[&str] = &[
+    "permit-X11-forwarding",
+    "permit-agent-forwarding",
+    "permit-port-forwarding",
+    PERMIT_PTY,
+    "permit-user-rc",
+];
+
+const ED25519: &str = "ssh-ed25519";
+const CERT_SUFFIX: &str = "-cert-v01@openssh.com";
+const USER_CERT: u32 = 1;
+const NONCE_LEN: usize = 32;
+// Certificates start a minute in the past so instances with a slightly slow clock accept them
+const CLOCK_SKEW_SECS: i64 = 60;
+
+// Subject key types that can be certified, with the number of wire fields after the type name
+fn subject_fields(key_type: &str) -> Option<usize> {
+    match key_type {
+        ED25519 => Some(1),
+        "ecdsa-sha2-nistp256" | "ecdsa-sha2-nistp384" | "ecdsa-sha2-nistp521" | "ssh-rsa" => Some(2),
+        _ => None,
+    }
+}
+
+fn invalid(message: impl Into<String>) -> KeyVaultError {
+    KeyVaultError::Validation(message.into())
+}
+
+// Splits an authorized_keys style line (`<type> <base64> [comment]`) into its type and blob
+fn decode_line(line: &str) -> KeyVaultResult<(String, Vec<u8>)> {
+    let mut parts = line.split_whitespace();
+    let (Some(key_type), Some(encoded)) = (parts.next(), parts.next()) else {
+        return Err(invalid("Expected an OpenSSH public key line"));
+    };
+    let blob = BASE64
+        .decode(encoded)
+        .map_err(|_| invalid("OpenSSH key data is not valid base64"))?;
+    if Reader::new(&blob).text()? != key_type {
+        return Err(invalid("OpenSSH key type doesn't match its data"));
+    }
+    Ok((key_type.to_string(), blob))
+}
+
+// The key's wire fields after its type name
+fn subject_key(public_key: &str) -> KeyVaultResult<(String, Vec<u8>)> {
+    let (key_type, blob) = decode_line(public_key)?;
+    let fields = subject_fields(&key_type)
+        .ok_or_else(|| invalid(format!("Unsupported SSH key type {}", key_type)))?;
+    let mut reader = Reader::new(&blob);
+    reader.text()?;
+    let start = reader.position();
+    for _ in 0..fields {
+        reader.string()?;
+    }
+    if !reader.is_empty() {
+        return Err(invalid("OpenSSH key has trailing data"));
+    }
+    Ok((key_type, blob[start..].to_vec()))
+}
+
+fn ed25519_blob(raw: &[u8]) -> Vec<u8> {
+    let mut writer = Writer::new();
+    writer.string(ED25519).string(raw);
+    writer.into_bytes()
+}
+
+fn ed25519_from_blob(blob: &[u8]) -> KeyVaultResult<Vec<u8>> {
+    let mut reader = Reader::new(blob);
+    if reader.text()? != ED25519 {
+        return Err(invalid("Only Ed25519 CA keys are supported"));
+    }
+    let raw = reader.string()?.to_vec();
+    if !reader.is_empty() {
+        return Err(invalid("Ed25519 key has trailing data"));
+    }
+    Ok(raw)
+}
+
+fn raw_public_key(der: &[u8]) -> KeyVaultResult<Vec<u8>> {
+    PKey::public_key_from_der(der)
+        .and_then(|key| key.raw_public_key())
+        .map_err(|e| KeyVaultError::Key(format!("Failed to decode CA public key: {}", e)))
+}
+
+fn timestamp(secs: u64) -> DateTime<Utc> {
+    // `u64::MAX` is OpenSSH's "forever"
+    i64::try_from(secs)
+        .ok()
+        .and_then(|secs| DateTime::from_timestamp(secs, 0))
+        .unwrap_or(DateTime::<Utc>::MAX_UTC)
+}
+
+fn random_bytes<const N: usize>() -> KeyVaultResult<[u8; N]> {
+    let mut bytes = [0u8; N];
+    openssl::rand::rand_bytes(&mut bytes)
+        .map_err(|e| KeyVaultError::Internal(format!("Failed to generate random bytes: {}", e)))?;
+    Ok(bytes)
+}
+
+#[derive(Debug, Clone)]
+pub struct CertificateRequest {
+    // The operator's OpenSSH public key line
+    pub public_key: String,
+    // Shows up in the instance's sshd log, so it should identify the user and session
+    pub key_id: String,
+    // Logins the certificate is valid for
+    pub principals: Vec<String>,
+    pub ttl: Duration,
+    pub force_command: Option<String>,
+    // CIDRs the certificate may be used from
+    pub source_addresses: Vec<String>,
+    pub extensions: BTreeSet<String>,
+}
+
+impl CertificateRequest {
+    // Only a terminal is permitted by default; forwarding has to be asked for
+    pub fn new(public_key: impl Into<String>, key_id: impl Into<String>, principals: Vec<String>, ttl: Duration) -> Self {
+        Self {
+            public_key: public_key.into(),
+            key_id: key_id.into(),
+            principals,
+            ttl,
+            force_command: None,
+            source_addresses: Vec::new(),
+            extensions: BTreeSet::from([PERMIT_PTY.to_string()]),
+        }
+    }
+
+    pub fn with_force_command(mut self, command: impl Into<String>) -> Self {
+        self.force_command = Some(command.into());
+        self
+    }
+
+    pub fn with_source_addresses(mut self, addresses: Vec<String>) -> Self {
+        self.source_addresses = addresses;
+        self
+    }
+
+    pub fn with_extension(mut self, extension: impl Into<String>) -> Self {
+        self.extensions.insert(extension.into());
+        self
+    }
+
+    fn validate(&self) -> KeyVaultResult<()> {
+        if self.principals.is_empty() || self.principals.iter().any(|p| p.is_empty() || p.contains(',')) {
+            return Err(invalid("Certificates need at least one principal, without commas"));
+        }
+        if self.key_id.is_empty() {
+            return Err(invalid("Certificates need a key id"));
+        }
+        if self.ttl <= Duration::zero() || self.ttl > Duration::minutes(MAX_CERTIFICATE_TTL_MINUTES) {
+            return Err(invalid(format!(
+                "Certificate lifetime must be between 1 second and {} minutes",
+                MAX_CERTIFICATE_TTL_MINUTES
+            )));
+        }
+        if self.force_command.as_deref().is_some_and(|c| c.trim().is_empty() || c.contains('\n')) {
+            return Err(invalid("Forced commands must be a single non-empty line"));
+        }
+        if self.source_addresses.iter().any(|a| a.is_empty() || a.contains(',') || a.contains(char::is_whitespace)) {
+            return Err(invalid("Source addresses must be single addresses or CIDRs"));
+        }
+        if let Some(unknown) = self.extensions.iter().find(|e| !KNOWN_EXTENSIONS.contains(&e.as_str())) {
+            return Err(invalid(format!("Unknown certificate extension {}", unknown)));
+        }
+        Ok(())
+    }
+
+    // Critical options, sorted by name as OpenSSH requires
+    fn critical_options(&self) -> BTreeMap<String, String> {
+        let mut options = BTreeMap::new();
+        if let Some(command) = &self.force_command {
+            options.insert(FORCE_COMMAND.to_string(), command.clone());
+        }
+        if !self.source_addresses.is_empty() {
+            options.insert(SOURCE_ADDRESS.to_string(), self.source_addresses.join(","));
+        }
+        options
+    }
+}
+
+// An OpenSSH user certificate, as issued or parsed back from its `-cert.pub` line
+#[derive(Debug, Clone, Serialize)]
+pub struct SshCertificate {
+    pub key_type: String,
+    pub serial: u64,
+    pub key_id: String,
+    pub principals: Vec<String>,
+    pub valid_after: DateTime<Utc>,
+    pub valid_before: DateTime<Utc>,
+    pub critical_options: BTreeMap<String, String>,
+    pub extensions: BTreeSet<String>,
+    // The certified key as an authorized_keys line, without a comment
+    pub public_key: String,
+    // The certificate as a `-cert.pub` line, ready to hand to `ssh -o CertificateFile=`
+    pub encoded: String,
+    #[serde(skip)]
+    signed: Vec<u8>,
+    #[serde(skip)]
+    signature_key: Vec<u8>,
+    #[serde(skip)]
+    signature: Vec<u8>,
+}
+
+impl SshCertificate {
+    pub fn parse(line: &str) -> KeyVaultResult<Self> {
+        let (cert_type, blob) = decode_line(line)?;
+        let base_type = cert_type
+            .strip_suffix(CERT_SUFFIX)
+            .ok_or_else(|| invalid(format!("{} is not an OpenSSH certificate type", cert_type)))?;
+        let fields = subject_fields(base_type).ok_or_else(|| invalid(format!("Unsupported SSH key type {}", base_type)))?;
+
+        let mut reader = Reader::new(&blob);
+        reader.text()?;
+        reader.string()?;
+        let start = reader.position();
+        for _ in 0..fields {
+            reader.string()?;
+        }
+        let mut subject = Writer::new();
+        subject.string(base_type).raw(&blob[start..reader.position()]);
+        let serial = reader.u64()?;
+        if reader.u32()? != USER_CERT {
+            return Err(invalid("Only user certificates are supported"));
+        }
+        let key_id = reader.text()?.to_string();
+        let mut principals = Vec::new();
+        let mut packed = Reader::new(reader.string()?);
+        while !packed.is_empty() {
+            principals.push(packed.text()?.to_string());
+        }
+        let valid_after = timestamp(reader.u64()?);
+        let valid_before = timestamp(reader.u64()?);
+        let mut critical_options = BTreeMap::new();
+        let mut packed = Reader::new(reader.string()?);
+        while !packed.is_empty() {
+            let name = packed.text()?.to_string();
+            let mut data = Reader::new(packed.string()?);
+            let value = if data.is_empty() { String::new() } else { data.text()?.to_string() };
+            critical_options.insert(name, value);
+        }
+        let mut extensions = BTreeSet::new();
+        let mut packed = Reader::new(reader.string()?);
+        while !packed.is_empty() {
+            extensions.insert(packed.text()?.to_string());
+            packed.string()?;
+        }
+        reader.string()?;
+        let signature_key = reader.string()?.to_vec();
+        let signed = blob[..reader.position()].to_vec();
+        let signature = reader.string()?.to_vec();
+        if !reader.is_empty() {
+            return Err(invalid("OpenSSH certificate has trailing data"));
+        }
+
+        Ok(Self {
+            key_type: cert_type,
+            serial,
+            key_id,
+            principals,
+            valid_after,
+            valid_before,
+            critical_options,
+            extensions,
+            public_key: format!("{} {}", base_type, BASE64.encode(subject.into_bytes())),
+            encoded: line.split_whitespace().take(2).collect::<Vec<_>>().join(" "),
+            signed,
+            signature_key,
+            signature,
+        })
+    }
+
+    pub fn force_command(&self) -> Option<&str> {
+        self.critical_options.get(FORCE_COMMAND).map(String::as_str)
+    }
+
+    pub fn valid_at(&self, at: DateTime<Utc>) -> bool {
+        self.valid_after <= at && at < self.valid_before
+    }
+
+    // Whether `ca_public_key` (an authorized_keys line) issued this certificate
+    pub fn signed_by(&self, ca_public_key: &str) -> KeyVaultResult<bool> {
+        let (_, ca_blob) = decode_line(ca_public_key)?;
+        if ca_blob != self.signature_key {
+            return Ok(false);
+        }
+        let mut signature = Reader::new(&self.signature);
+        if signature.text()? != ED25519 {
+            return Ok(false);
+        }
+        let signature = signature.string()?;
+        let key = PKey::public_key_from_raw_bytes(&ed25519_from_blob(&ca_blob)?, Id::ED25519)
+            .map_err(|e| KeyVaultError::Key(format!("Invalid CA key: {}", e)))?;
+        Ok(Verifier::new_without_digest(&key)
+            .and_then(|mut verifier| verifier.verify_oneshot(signature, &self.signed))
+            .unwrap_or(false))
+    }
+}
+
+// Signs SSH user certificates with an Ed25519 key in the signing service. `principal` is the
+// vault identity the CA acts as; it needs read and sign access to the key.
+pub struct SshCertificateAuthority {
+    signing: Arc<SigningService>,
+    principal: String,
+    key_name: String,
+}
+
+impl SshCertificateAuthority {
+    pub fn new(signing: Arc<SigningService>, principal: impl Into<String>) -> Self {
+        Self {
+            signing,
+            principal: principal.into(),
+            key_name: DEFAULT_CA_KEY.to_string(),
+        }
+    }
+
+    pub fn with_key_name(mut self, key_name: impl Into<String>) -> Self {
+        self.key_name = key_name.into();
+        self
+    }
+
+    // Creates the CA key on first use; the principal also needs write access for this
+    pub async fn ensure_key(&self) -> KeyVaultResult<()> {
+        match self.signing.get_key(&self.principal, &self.key_name).await {
+            Ok(_) => Ok(()),
+            Err(KeyVaultError::NotFound(_)) => {
+                match self.signing.create_key(&self.principal, &self.key_name, SigningAlgorithm::Ed25519).await {
+                    Ok(_) | Err(KeyVaultError::Conflict(_)) => Ok(()),
+                    Err(e) => Err(e),
+                }
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    // Every CA key instances should trust, current first. After a rotation the previous key stays
+    // listed until it's revoked, so certificates already issued keep working.
+    pub async fn trusted_keys(&self) -> KeyVaultResult<Vec<String>> {
+        let info = self.signing.get_key(&self.principal, &self.key_name).await?;
+        if info.algorithm != SigningAlgorithm::Ed25519 {
+            return Err(KeyVaultError::Config(format!("SSH CA key {} must be Ed25519", self.key_name)));
+        }
+        let mut versions: Vec<_> = info.versions.iter().filter(|v| v.state != KeyVersionState::Revoked).collect();
+        versions.sort_by(|a, b| b.version.cmp(&a.version));
+        versions
+            .into_iter()
+            .map(|v| {
+                let blob = ed25519_blob(&raw_public_key(&v.public_key_der)?);
+                Ok(format!("{} {} {}-v{}", ED25519, BASE64.encode(blob), self.key_name, v.version))
+            })
+            .collect()
+    }
+
+    pub async fn issue(&self, request: &CertificateRequest) -> KeyVaultResult<SshCertificate> {
+        request.validate()?;
+        let (key_type, subject) = subject_key(&request.public_key)?;
+        let cert_type = format!("{}{}", key_type, CERT_SUFFIX);
+        let now = Utc::now();
+        let valid_after = (now - Duration::seconds(CLOCK_SKEW_SECS)).timestamp() as u64;
+        let valid_before = (now + request.ttl).timestamp() as u64;
+        let serial = u64::from_be_bytes(random_bytes()?);
+        let nonce: [u8; NONCE_LEN] = random_bytes()?;
+
+        let mut principals = Writer::new();
+        for principal in &request.principals {
+            principals.string(principal);
+        }
+        let mut options = Writer::new();
+        for (name, value) in request.critical_options() {
+            let mut data = Writer::new();
+            data.string(value);
+            options.string(name).string(data.into_bytes());
+        }
+        let mut extensions = Writer::new();
+        for extension in &request.extensions {
+            extensions.string(extension).string(b"");
+        }
+        let (principals, options, extensions) = (principals.into_bytes(), options.into_bytes(), extensions.into_bytes());
+
+        // The CA key is part of the signed data, so a rotation landing between reading the key and
+        // signing would produce a certificate naming the wrong key; sign again if that happens
+        for _ in 0..2 {
+            let info = self.signing.get_key(&self.principal, &self.key_name).await?;
+            if info.algorithm != SigningAlgorithm::Ed25519 {
+                return Err(KeyVaultError::Config(format!("SSH CA key {} must be Ed25519", self.key_name)));
+            }
+            let current = info
+                .versions
+                .iter()
+                .find(|v| v.version == info.current_version)
+                .ok_or_else(|| KeyVaultError::Internal(format!("SSH CA key {} has no current version", self.key_name)))?;
+            let mut tbs = Writer::new();
+            tbs.string(&cert_type)
+                .string(nonce)
+                .raw(&subject)
+                .u64(serial)
+                .u32(USER_CERT)
+                .string(&request.key_id)
+                .string(&principals)
+                .u64(valid_after)
+                .u64(valid_before)
+                .string(&options)
+                .string(&extensions)
+                .string(b"")
+                .string(ed25519_blob(&raw_public_key(&current.public_key_der)?));
+            let tbs = tbs.into_bytes();
+
+            let signature = self
+                .signing
+                .sign(&self.principal, &self.key_name, SigningInput::Raw(tbs.clone()))
+                .await?;
+            if signature.key_version != current.version {
+                continue;
+            }
+            let mut signature_blob = Writer::new();
+            signature_blob.string(ED25519).string(&signature.bytes);
+            let mut certificate = Writer::new();
+            certificate.raw(&tbs).string(signature_blob.into_bytes());
+
+            info!(
+                "Issued SSH certificate {} ({}) for {:?}, valid {}s",
+                serial,
+                request.key_id,
+                request.principals,
+                request.ttl.num_seconds()
+            );
+            return SshCertificate::parse(&format!("{} {}", cert_type, BASE64.encode(certificate.into_bytes())));
+        }
+        Err(KeyVaultError::Conflict(format!("SSH CA key {} rotated while signing", self.key_name)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secret::{AccessPolicy, AccessPolicyManager, InMemoryAccessPolicyManager, InMemoryAuditLogger, SecretAction, SecretPermission};
+
+    async fn authority() -> (SshCertificateAuthority, Arc<SigningService>) {
+        let policies = InMemoryAccessPolicyManager::new();
+        policies
+            .create_policy(AccessPolicy {
+                id: "ssh-ca".into(),
+                name: "ssh-ca".into(),
+                description: None,
+                principals: vec!["svc-bastion".into()],
+                permissions: vec![SecretPermission {
+                    actions: vec![SecretAction::Read, SecretAction::Write, SecretAction::Rotate, SecretAction::Sign],
+                    secret_patterns: vec!["signing/*".into()],
+                }],
+                conditions: None,
+            })
+            .await
+            .unwrap();
+        let signing = Arc::new(SigningService::new(Arc::new(policies), Arc::new(InMemoryAuditLogger::new())));
+        let authority = SshCertificateAuthority::new(signing.clone(), "svc-bastion");
+        authority.ensure_key().await.unwrap();
+        (authority, signing)
+    }
+
+    fn operator_key() -> String {
+        let key = PKey::generate_ed25519().unwrap();
+        format!("{} {} alice@laptop", ED25519, BASE64.encode(ed25519_blob(&key.raw_public_key().unwrap())))
+    }
+
+    #[tokio::test]
+    async fn test_certificate_constraints() {
+        let (authority, _) = authority().await;
+        let public_key = operator_key();
+        let request = CertificateRequest::new(&public_key, "sirsi:alice:s-1", vec!["ec2-user".into()], Duration::minutes(5))
+            .with_force_command("/usr/bin/tail -f /var/log/app.log")
+            .with_source_addresses(vec!["10.0.0.0/8".into()]);
+        let issued = authority.issue(&request).await.unwrap();
+
+        // Round-trips through the line an operator would save as id_ed25519-cert.pub
+        let cert = SshCertificate::parse(&issued.encoded).unwrap();
+        assert_eq!(cert.key_type, "ssh-ed25519-cert-v01@openssh.com");
+        assert_eq!(cert.principals, ["ec2-user"]);
+        assert_eq!(cert.key_id, "sirsi:alice:s-1");
+        assert_eq!(cert.public_key, public_key.rsplit_once(' ').unwrap().0);
+        assert_eq!(cert.force_command(), Some("/usr/bin/tail -f /var/log/app.log"));
+        assert_eq!(cert.critical_options[SOURCE_ADDRESS], "10.0.0.0/8");
+        assert_eq!(cert.extensions, BTreeSet::from([PERMIT_PTY.to_string()]));
+        let lifetime = cert.valid_before - cert.valid_after;
+        assert_eq!(lifetime, Duration::minutes(5) + Duration::seconds(CLOCK_SKEW_SECS));
+        assert!(cert.valid_at(Utc::now()));
+        assert!(!cert.valid_at(Utc::now() + Duration::minutes(6)));
+
+        let trusted = authority.trusted_keys().await.unwrap();
+        assert!(cert.signed_by(&trusted[0]).unwrap());
+        assert!(!cert.signed_by(&operator_key()).unwrap());
+
+        // Lifetimes over the cap, unknown extensions and multi-line commands are refused
+        let too_long = CertificateRequest::new(&public_key, "k", vec!["ec2-user".into()], Duration::minutes(61));
+        assert!(matches!(authority.issue(&too_long).await, Err(KeyVaultError::Validation(_))));
+        let forwarding = CertificateRequest::new(&public_key, "k", vec!["ec2-user".into()], Duration::minutes(5))
+            .with_extension("permit-everything");
+        assert!(matches!(authority.issue(&forwarding).await, Err(KeyVaultError::Validation(_))));
+        let injected = CertificateRequest::new(&public_key, "k", vec!["ec2-user".into()], Duration::minutes(5))
+            .with_force_command("ls\nrm -rf /");
+        assert!(matches!(authority.issue(&injected).await, Err(KeyVaultError::Validation(_))));
+    }
+
+    #[tokio::test]
+    async fn test_rotation_keeps_previous_key_trusted() {
+        let (authority, signing) = authority().await;
+        let request = CertificateRequest::new(operator_key(), "k", vec!["ubuntu".into()], Duration::minutes(1));
+        let before = authority.issue(&request).await.unwrap();
+
+        signing.rotate_key("svc-bastion", DEFAULT_CA_KEY).await.unwrap();
+        let trusted = authority.trusted_keys().await.unwrap();
+        assert_eq!(trusted.len(), 2);
+        assert!(before.signed_by(&trusted[1]).unwrap());
+        let after = authority.issue(&request).await.unwrap();
+        assert!(after.signed_by(&trusted[0]).unwrap());
+        assert!(!after.signed_by(&trusted[1]).unwrap());
+
+        signing.revoke_version("svc-bastion", DEFAULT_CA_KEY, 1).await.unwrap();
+        assert_eq!(authority.trusted_keys().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_unauthorized_principal_cannot_issue() {
+        let (authority, signing) = authority().await;
+        let intruder = SshCertificateAuthority::new(signing, "intruder");
+        let request = CertificateRequest::new(operator_key(), "k", vec!["root".into()], Duration::minutes(1));
+        assert!(authority.issue(&request).await.is_ok());
+        assert!(matches!(intruder.issue(&request).await, Err(KeyVaultError::Permission(_))));
+    }
+}
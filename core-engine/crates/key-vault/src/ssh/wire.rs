@@ -0,0 +1,90 @@
+// SSH wire encoding (RFC 4251 section 5): big-endian integers and length-prefixed strings
+
+use crate::error::{KeyVaultError, KeyVaultResult};
+
+#[derive(Default)]
+pub(crate) struct Writer {
+    buf: Vec<u8>,
+}
+
+impl Writer {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn u32(&mut self, value: u32) -> &mut Self {
+        self.buf.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    pub(crate) fn u64(&mut self, value: u64) -> &mut Self {
+        self.buf.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    pub(crate) fn string(&mut self, value: impl AsRef<[u8]>) -> &mut Self {
+        let value = value.as_ref();
+        self.u32(value.len() as u32);
+        self.buf.extend_from_slice(value);
+        self
+    }
+
+    // Appends already-encoded fields as they are
+    pub(crate) fn raw(&mut self, value: &[u8]) -> &mut Self {
+        self.buf.extend_from_slice(value);
+        self
+    }
+
+    pub(crate) fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+fn truncated() -> KeyVaultError {
+    KeyVaultError::Validation("SSH data is truncated".into())
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> KeyVaultResult<&'a [u8]> {
+        let end = self.pos.checked_add(len).filter(|end| *end <= self.data.len()).ok_or_else(truncated)?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    pub(crate) fn u32(&mut self) -> KeyVaultResult<u32> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().map_err(|_| truncated())?))
+    }
+
+    pub(crate) fn u64(&mut self) -> KeyVaultResult<u64> {
+        Ok(u64::from_be_bytes(self.take(8)?.try_into().map_err(|_| truncated())?))
+    }
+
+    pub(crate) fn string(&mut self) -> KeyVaultResult<&'a [u8]> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+
+    pub(crate) fn text(&mut self) -> KeyVaultResult<&'a str> {
+        std::str::from_utf8(self.string()?)
+            .map_err(|_| KeyVaultError::Validation("SSH string is not valid UTF-8".into()))
+    }
+
+    // Bytes consumed so far, for slicing out the signed part of a certificate
+    pub(crate) fn position(&self) -> usize {
+        self.pos
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.pos == self.data.len()
+    }
+}
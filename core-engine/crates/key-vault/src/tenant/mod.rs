@@ -0,0 +1,637 @@
+//! Tenant master keys. A tenant's sensitive fields are sealed with a data key derived from the
+//! current version of its master key, which is generated here or imported from the tenant (BYOK).
+//! Revoking a tenant key destroys every version, leaving its data unreadable.
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD as BASE64;
+use base64::Engine;
+use chrono::{DateTime, Duration, Utc};
+use openssl::md::Md;
+use openssl::pkey::{PKey, Private};
+use openssl::pkey_ctx::PkeyCtx;
+use openssl::rsa::{Padding, Rsa};
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::hkdf;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+use zeroize::Zeroizing;
+
+use crate::crypto::{EncryptionKeyState, DATA_KEY_LEN};
+use crate::error::{KeyVaultError, KeyVaultResult};
+use crate::secret::{AccessPolicyManager, AuditEvent, AuditLogger, SecretAction};
+
+pub mod reencrypt;
+
+pub use reencrypt::{
+    EncryptedField, EncryptedFieldSource, InMemoryReencryptionJobStore, ReencryptionJob, ReencryptionJobStore,
+    ReencryptionStatus, Reencryptor, SourceProgress,
+};
+
+pub const FIELD_CIPHERTEXT_PREFIX: &str = "sirsi:v1";
+pub const IMPORT_KEY_BITS: u32 = 3072;
+
+const FIELD_KEY_INFO: &[u8] = b"sirsi field encryption";
+const IMPORT_TTL_MINUTES: i64 = 60;
+const REVOCATION_TTL_MINUTES: i64 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TenantKeyOrigin {
+    Generated,
+    Imported,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TenantKeyVersionInfo {
+    pub version: u32,
+    pub origin: TenantKeyOrigin,
+    pub state: EncryptionKeyState,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TenantKeyInfo {
+    pub tenant: String,
+    pub current_version: u32,
+    pub versions: Vec<TenantKeyVersionInfo>,
+    // Set once the key has been shredded; the tenant can't get another
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+// What a tenant needs to bring its own key: its 32 bytes of key material wrapped with
+// RSA-OAEP-SHA256 under `public_key_pem`, sent back with the token before `expires_at`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportParameters {
+    pub tenant: String,
+    pub import_token: String,
+    pub public_key_pem: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+// Revocation can't be undone, so it takes a second call echoing `confirmation`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RevocationChallenge {
+    pub tenant: String,
+    pub token: String,
+    pub confirmation: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+// `sirsi:v1:<tenant>:<key version>:<base64url(nonce || AES-256-GCM(plaintext))>`, stored in place
+// of the field. The key version is readable so re-encryption can find stale rows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldCiphertext {
+    pub tenant: String,
+    pub key_version: u32,
+    pub sealed: Vec<u8>,
+}
+
+impl FieldCiphertext {
+    pub fn is_ciphertext(value: &str) -> bool {
+        value.starts_with(FIELD_CIPHERTEXT_PREFIX)
+    }
+}
+
+impl fmt::Display for FieldCiphertext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}:{}", FIELD_CIPHERTEXT_PREFIX, self.tenant, self.key_version, BASE64.encode(&self.sealed))
+    }
+}
+
+impl FromStr for FieldCiphertext {
+    type Err = KeyVaultError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let malformed = || KeyVaultError::Validation("Malformed field ciphertext".into());
+        let rest = value
+            .strip_prefix(FIELD_CIPHERTEXT_PREFIX)
+            .and_then(|rest| rest.strip_prefix(':'))
+            .ok_or_else(malformed)?;
+        let mut parts = rest.splitn(3, ':');
+        let (Some(tenant), Some(version), Some(sealed)) = (parts.next(), parts.next(), parts.next()) else {
+            return Err(malformed());
+        };
+        let sealed = BASE64.decode(sealed).map_err(|_| malformed())?;
+        if sealed.len() < NONCE_LEN {
+            return Err(malformed());
+        }
+        Ok(Self {
+            tenant: tenant.to_string(),
+            key_version: version.parse().map_err(|_| malformed())?,
+            sealed,
+        })
+    }
+}
+
+struct KeyVersion {
+    info: TenantKeyVersionInfo,
+    material: Zeroizing<Vec<u8>>,
+}
+
+struct TenantKey {
+    versions: Vec<KeyVersion>,
+    revoked_at: Option<DateTime<Utc>>,
+}
+
+impl TenantKey {
+    fn current(&self) -> &KeyVersion {
+        self.versions.last().expect("tenant keys always have a version")
+    }
+
+    fn info(&self, tenant: &str) -> TenantKeyInfo {
+        TenantKeyInfo {
+            tenant: tenant.to_string(),
+            current_version: self.current().info.version,
+            versions: self.versions.iter().map(|v| v.info.clone()).collect(),
+            revoked_at: self.revoked_at,
+        }
+    }
+
+    fn add_version(&mut self, origin: TenantKeyOrigin, material: Zeroizing<Vec<u8>>) {
+        for version in self.versions.iter_mut() {
+            if version.info.state == EncryptionKeyState::Active {
+                version.info.state = EncryptionKeyState::DecryptOnly;
+            }
+        }
+        let version = self.current().info.version + 1;
+        self.versions.push(KeyVersion { info: version_info(version, origin), material });
+    }
+}
+
+struct PendingImport {
+    tenant: String,
+    key: PKey<Private>,
+    expires_at: DateTime<Utc>,
+}
+
+struct PendingRevocation {
+    token: String,
+    confirmation: String,
+    requested_by: String,
+    expires_at: DateTime<Utc>,
+}
+
+#[derive(Default)]
+struct TenantKeys {
+    keys: HashMap<String, TenantKey>,
+    imports: HashMap<String, PendingImport>,
+    revocations: HashMap<String, PendingRevocation>,
+}
+
+fn resource_id(tenant: &str) -> String {
+    format!("tenant-keys/{}", tenant)
+}
+
+fn validate_tenant(tenant: &str) -> KeyVaultResult<()> {
+    let valid = !tenant.is_empty() && tenant.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(KeyVaultError::Validation(format!("Invalid tenant id '{}'", tenant)))
+    }
+}
+
+fn version_info(version: u32, origin: TenantKeyOrigin) -> TenantKeyVersionInfo {
+    TenantKeyVersionInfo {
+        version,
+        origin,
+        state: EncryptionKeyState::Active,
+        created_at: Utc::now(),
+    }
+}
+
+fn random_bytes(rng: &SystemRandom, len: usize) -> KeyVaultResult<Zeroizing<Vec<u8>>> {
+    let mut bytes = Zeroizing::new(vec![0u8; len]);
+    rng.fill(&mut bytes)
+        .map_err(|_| KeyVaultError::Key("System random source failed".into()))?;
+    Ok(bytes)
+}
+
+// The field key is never stored: it's derived from the master key version, salted with the tenant
+fn field_key(tenant: &str, material: &[u8]) -> KeyVaultResult<LessSafeKey> {
+    let prk = hkdf::Salt::new(hkdf::HKDF_SHA256, tenant.as_bytes()).extract(material);
+    let okm = prk
+        .expand(&[FIELD_KEY_INFO], &AES_256_GCM)
+        .map_err(|_| KeyVaultError::Internal("Field key derivation failed".into()))?;
+    Ok(LessSafeKey::new(UnboundKey::from(okm)))
+}
+
+// Binds a ciphertext to its tenant, key version and the field it was written to
+fn field_aad(tenant: &str, version: u32, context: &str) -> Vec<u8> {
+    format!("{}:{}:{}", tenant, version, context).into_bytes()
+}
+
+fn unwrap_imported(key: &PKey<Private>, wrapped: &[u8]) -> KeyVaultResult<Zeroizing<Vec<u8>>> {
+    let failed = |e: openssl::error::ErrorStack| KeyVaultError::Key(format!("Failed to unwrap imported key material: {}", e));
+    let mut ctx = PkeyCtx::new(key).map_err(failed)?;
+    ctx.decrypt_init().map_err(failed)?;
+    ctx.set_rsa_padding(Padding::PKCS1_OAEP).map_err(failed)?;
+    ctx.set_rsa_oaep_md(Md::sha256()).map_err(failed)?;
+    ctx.set_rsa_mgf1_md(Md::sha256()).map_err(failed)?;
+    let mut material = Zeroizing::new(Vec::new());
+    ctx.decrypt_to_vec(wrapped, &mut material).map_err(failed)?;
+    if material.len() != DATA_KEY_LEN {
+        return Err(KeyVaultError::Validation(format!(
+            "Imported key material must be {} bytes, got {}",
+            DATA_KEY_LEN,
+            material.len()
+        )));
+    }
+    Ok(material)
+}
+
+// Key management is authorized and audited per call like the other key services. Field
+// encryption is the platform's own use of the keys and isn't, since it runs for every row.
+pub struct TenantKeyService {
+    state: RwLock<TenantKeys>,
+    policies: Arc<dyn AccessPolicyManager>,
+    audit: Arc<dyn AuditLogger>,
+    rng: SystemRandom,
+}
+
+impl TenantKeyService {
+    pub fn new(policies: Arc<dyn AccessPolicyManager>, audit: Arc<dyn AuditLogger>) -> Self {
+        Self {
+            state: RwLock::new(TenantKeys::default()),
+            policies,
+            audit,
+            rng: SystemRandom::new(),
+        }
+    }
+
+    async fn authorize(&self, principal: &str, tenant: &str, action: SecretAction) -> KeyVaultResult<()> {
+        validate_tenant(tenant)?;
+        if self.policies.validate_access(principal, &resource_id(tenant), action).await? {
+            Ok(())
+        } else {
+            Err(KeyVaultError::Permission(format!("{} may not {:?} the key of tenant {}", principal, action, tenant)))
+        }
+    }
+
+    async fn audited<T>(&self, principal: &str, tenant: &str, action: SecretAction, result: KeyVaultResult<T>) -> KeyVaultResult<T> {
+        self.audit
+            .log_event(AuditEvent {
+                id: uuid::Uuid::new_v4().to_string(),
+                timestamp: Utc::now(),
+                principal: principal.to_string(),
+                action,
+                secret_id: resource_id(tenant),
+                success: result.is_ok(),
+                error: result.as_ref().err().map(|e| e.to_string()),
+                metadata: HashMap::new(),
+            })
+            .await?;
+        result
+    }
+
+    pub async fn create_tenant_key(&self, principal: &str, tenant: &str) -> KeyVaultResult<TenantKeyInfo> {
+        let result = self.create_inner(principal, tenant).await;
+        self.audited(principal, tenant, SecretAction::Write, result).await
+    }
+
+    async fn create_inner(&self, principal: &str, tenant: &str) -> KeyVaultResult<TenantKeyInfo> {
+        self.authorize(principal, tenant, SecretAction::Write).await?;
+        let material = random_bytes(&self.rng, DATA_KEY_LEN)?;
+        let mut state = self.state.write().await;
+        if state.keys.contains_key(tenant) {
+            return Err(KeyVaultError::Conflict(format!("Tenant {} already has a key", tenant)));
+        }
+        let key = TenantKey {
+            versions: vec![KeyVersion { info: version_info(1, TenantKeyOrigin::Generated), material }],
+            revoked_at: None,
+        };
+        let info = key.info(tenant);
+        state.keys.insert(tenant.to_string(), key);
+        info!("Created key for tenant {}", tenant);
+        Ok(info)
+    }
+
+    pub async fn tenant_key(&self, principal: &str, tenant: &str) -> KeyVaultResult<TenantKeyInfo> {
+        self.authorize(principal, tenant, SecretAction::Read).await?;
+        self.state
+            .read()
+            .await
+            .keys
+            .get(tenant)
+            .map(|key| key.info(tenant))
+            .ok_or_else(|| KeyVaultError::NotFound(format!("Tenant {} has no key", tenant)))
+    }
+
+    // Starts a BYOK import; the wrapping key pair only lives until the import or its expiry
+    pub async fn begin_import(&self, principal: &str, tenant: &str) -> KeyVaultResult<ImportParameters> {
+        self.authorize(principal, tenant, SecretAction::Write).await?;
+        let key = Rsa::generate(IMPORT_KEY_BITS)
+            .and_then(PKey::from_rsa)
+            .map_err(|e| KeyVaultError::Key(format!("Failed to generate import key: {}", e)))?;
+        let pem = key
+            .public_key_to_pem()
+            .map_err(|e| KeyVaultError::Key(format!("Failed to encode import key: {}", e)))?;
+        let params = ImportParameters {
+            tenant: tenant.to_string(),
+            import_token: uuid::Uuid::new_v4().to_string(),
+            public_key_pem: String::from_utf8_lossy(&pem).into_owned(),
+            expires_at: Utc::now() + Duration::minutes(IMPORT_TTL_MINUTES),
+        };
+        let mut state = self.state.write().await;
+        let now = Utc::now();
+        state.imports.retain(|_, pending| pending.expires_at > now);
+        state.imports.insert(
+            params.import_token.clone(),
+            PendingImport { tenant: tenant.to_string(), key, expires_at: params.expires_at },
+        );
+        Ok(params)
+    }
+
+    // Creates the tenant's key from the imported material, or adds it as a new version; existing
+    // data is then re-encrypted like after any rotation
+    pub async fn import_tenant_key(
+        &self,
+        principal: &str,
+        tenant: &str,
+        import_token: &str,
+        wrapped_material: &[u8],
+    ) -> KeyVaultResult<TenantKeyInfo> {
+        let result = self.import_inner(principal, tenant, import_token, wrapped_material).await;
+        self.audited(principal, tenant, SecretAction::Write, result).await
+    }
+
+    async fn import_inner(&self, principal: &str, tenant: &str, import_token: &str, wrapped: &[u8]) -> KeyVaultResult<TenantKeyInfo> {
+        self.authorize(principal, tenant, SecretAction::Write).await?;
+        let mut state = self.state.write().await;
+        let pending = state
+            .imports
+            .remove(import_token)
+            .filter(|pending| pending.tenant == tenant && pending.expires_at > Utc::now())
+            .ok_or_else(|| KeyVaultError::NotFound(format!("No pending key import {} for tenant {}", import_token, tenant)))?;
+        let material = unwrap_imported(&pending.key, wrapped)?;
+        let key = match state.keys.entry(tenant.to_string()) {
+            Entry::Occupied(entry) => {
+                let key = entry.into_mut();
+                if key.revoked_at.is_some() {
+                    return Err(KeyVaultError::Conflict(format!("The key of tenant {} has been revoked", tenant)));
+                }
+                key.add_version(TenantKeyOrigin::Imported, material);
+                key
+            }
+            Entry::Vacant(entry) => entry.insert(TenantKey {
+                versions: vec![KeyVersion { info: version_info(1, TenantKeyOrigin::Imported), material }],
+                revoked_at: None,
+            }),
+        };
+        info!("Imported version {} of the key of tenant {}", key.current().info.version, tenant);
+        Ok(key.info(tenant))
+    }
+
+    // Only adds a version; see `Reencryptor::rotate_tenant_key` for moving existing data onto it
+    pub async fn rotate_key(&self, principal: &str, tenant: &str) -> KeyVaultResult<TenantKeyInfo> {
+        let result = self.rotate_inner(principal, tenant).await;
+        self.audited(principal, tenant, SecretAction::Rotate, result).await
+    }
+
+    async fn rotate_inner(&self, principal: &str, tenant: &str) -> KeyVaultResult<TenantKeyInfo> {
+        self.authorize(principal, tenant, SecretAction::Rotate).await?;
+        let material = random_bytes(&self.rng, DATA_KEY_LEN)?;
+        let mut state = self.state.write().await;
+        let key = live_key_mut(&mut state.keys, tenant)?;
+        key.add_version(TenantKeyOrigin::Generated, material);
+        info!("Rotated the key of tenant {} to version {}", tenant, key.current().info.version);
+        Ok(key.info(tenant))
+    }
+
+    pub async fn request_revocation(&self, principal: &str, tenant: &str) -> KeyVaultResult<RevocationChallenge> {
+        self.authorize(principal, tenant, SecretAction::Delete).await?;
+        let mut state = self.state.write().await;
+        live_key_mut(&mut state.keys, tenant)?;
+        let challenge = RevocationChallenge {
+            tenant: tenant.to_string(),
+            token: uuid::Uuid::new_v4().to_string(),
+            confirmation: format!("destroy all data of tenant {}", tenant),
+            expires_at: Utc::now() + Duration::minutes(REVOCATION_TTL_MINUTES),
+        };
+        state.revocations.insert(
+            tenant.to_string(),
+            PendingRevocation {
+                token: challenge.token.clone(),
+                confirmation: challenge.confirmation.clone(),
+                requested_by: principal.to_string(),
+                expires_at: challenge.expires_at,
+            },
+        );
+        warn!("{} requested revocation of the key of tenant {}", principal, tenant);
+        Ok(challenge)
+    }
+
+    // Crypto-shredding: every version's material is zeroed, so nothing sealed under the tenant's
+    // key can be read again
+    pub async fn confirm_revocation(&self, principal: &str, tenant: &str, token: &str, confirmation: &str) -> KeyVaultResult<TenantKeyInfo> {
+        let result = self.revoke_inner(principal, tenant, token, confirmation).await;
+        self.audited(principal, tenant, SecretAction::Delete, result).await
+    }
+
+    async fn revoke_inner(&self, principal: &str, tenant: &str, token: &str, confirmation: &str) -> KeyVaultResult<TenantKeyInfo> {
+        self.authorize(principal, tenant, SecretAction::Delete).await?;
+        let mut state = self.state.write().await;
+        let pending = state
+            .revocations
+            .get(tenant)
+            .filter(|p| p.token == token && p.expires_at > Utc::now())
+            .ok_or_else(|| KeyVaultError::NotFound(format!("No pending revocation {} for tenant {}", token, tenant)))?;
+        if pending.requested_by != principal {
+            return Err(KeyVaultError::Permission(format!("Revocation {} has to be confirmed by the one who requested it", token)));
+        }
+        if pending.confirmation != confirmation {
+            return Err(KeyVaultError::Validation(format!("Type \"{}\" to confirm the revocation", pending.confirmation)));
+        }
+        state.revocations.remove(tenant);
+        let key = live_key_mut(&mut state.keys, tenant)?;
+        for version in key.versions.iter_mut() {
+            version.material = Zeroizing::new(Vec::new());
+            version.info.state = EncryptionKeyState::Revoked;
+        }
+        key.revoked_at = Some(Utc::now());
+        warn!("Revoked the key of tenant {}; its data can no longer be decrypted", tenant);
+        Ok(key.info(tenant))
+    }
+
+    // `context` names the field, e.g. `discovery_run_resources.attributes/<run>`; decrypting
+    // needs the same one
+    pub async fn encrypt_field(&self, tenant: &str, context: &str, plaintext: &[u8]) -> KeyVaultResult<String> {
+        validate_tenant(tenant)?;
+        let state = self.state.read().await;
+        let key = live_key(&state.keys, tenant)?;
+        let current = key.current();
+        let mut nonce = [0u8; NONCE_LEN];
+        self.rng
+            .fill(&mut nonce)
+            .map_err(|_| KeyVaultError::Key("System random source failed".into()))?;
+        let mut sealed = plaintext.to_vec();
+        field_key(tenant, &current.material)?
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(field_aad(tenant, current.info.version, context)),
+                &mut sealed,
+            )
+            .map_err(|_| KeyVaultError::Key(format!("Failed to encrypt field for tenant {}", tenant)))?;
+        let mut bytes = nonce.to_vec();
+        bytes.extend_from_slice(&sealed);
+        Ok(FieldCiphertext { tenant: tenant.to_string(), key_version: current.info.version, sealed: bytes }.to_string())
+    }
+
+    pub async fn decrypt_field(&self, tenant: &str, context: &str, ciphertext: &str) -> KeyVaultResult<Zeroizing<Vec<u8>>> {
+        let parsed: FieldCiphertext = ciphertext.parse()?;
+        if parsed.tenant != tenant {
+            return Err(KeyVaultError::Permission(format!("Field ciphertext belongs to another tenant than {}", tenant)));
+        }
+        let state = self.state.read().await;
+        let key = live_key(&state.keys, tenant)?;
+        let version = key
+            .versions
+            .iter()
+            .find(|v| v.info.version == parsed.key_version)
+            .ok_or_else(|| KeyVaultError::NotFound(format!("Key of tenant {} has no version {}", tenant, parsed.key_version)))?;
+        let nonce: [u8; NONCE_LEN] = parsed.sealed[..NONCE_LEN].try_into().expect("slice is nonce length");
+        let mut sealed = Zeroizing::new(parsed.sealed[NONCE_LEN..].to_vec());
+        let plaintext = field_key(tenant, &version.material)?
+            .open_in_place(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(field_aad(tenant, parsed.key_version, context)),
+                &mut sealed,
+            )
+            .map_err(|_| KeyVaultError::Key(format!("Field ciphertext does not open under the key of tenant {}", tenant)))?;
+        Ok(Zeroizing::new(plaintext.to_vec()))
+    }
+
+    // Re-seals a field under the current key version; ciphertexts already on it come back as None
+    pub async fn reencrypt_field(&self, tenant: &str, context: &str, ciphertext: &str) -> KeyVaultResult<Option<String>> {
+        let parsed: FieldCiphertext = ciphertext.parse()?;
+        let current = {
+            let state = self.state.read().await;
+            live_key(&state.keys, tenant)?.current().info.version
+        };
+        if parsed.key_version >= current {
+            return Ok(None);
+        }
+        let plaintext = self.decrypt_field(tenant, context, ciphertext).await?;
+        self.encrypt_field(tenant, context, &plaintext).await.map(Some)
+    }
+}
+
+fn live_key<'a>(keys: &'a HashMap<String, TenantKey>, tenant: &str) -> KeyVaultResult<&'a TenantKey> {
+    match keys.get(tenant) {
+        Some(key) if key.revoked_at.is_some() => Err(KeyVaultError::Key(format!("The key of tenant {} has been revoked", tenant))),
+        Some(key) => Ok(key),
+        None => Err(KeyVaultError::NotFound(format!("Tenant {} has no key", tenant))),
+    }
+}
+
+fn live_key_mut<'a>(keys: &'a mut HashMap<String, TenantKey>, tenant: &str) -> KeyVaultResult<&'a mut TenantKey> {
+    match keys.get_mut(tenant) {
+        Some(key) if key.revoked_at.is_some() => Err(KeyVaultError::Key(format!("The key of tenant {} has been revoked", tenant))),
+        Some(key) => Ok(key),
+        None => Err(KeyVaultError::NotFound(format!("Tenant {} has no key", tenant))),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::secret::{AccessPolicy, InMemoryAccessPolicyManager, InMemoryAuditLogger, SecretPermission};
+
+    pub(crate) async fn service() -> TenantKeyService {
+        let policies = InMemoryAccessPolicyManager::new();
+        policies
+            .create_policy(AccessPolicy {
+                id: "tenant-keys".into(),
+                name: "tenant-keys".into(),
+                description: None,
+                principals: vec!["svc-platform".into(), "security-admin".into()],
+                permissions: vec![SecretPermission {
+                    actions: vec![SecretAction::Read, SecretAction::Write, SecretAction::Rotate, SecretAction::Delete],
+                    secret_patterns: vec!["tenant-keys/*".into()],
+                }],
+                conditions: None,
+            })
+            .await
+            .unwrap();
+        TenantKeyService::new(Arc::new(policies), Arc::new(InMemoryAuditLogger::new()))
+    }
+
+    #[tokio::test]
+    async fn test_tenant_keys_are_isolated() {
+        let service = service().await;
+        service.create_tenant_key("svc-platform", "acme").await.unwrap();
+        service.create_tenant_key("svc-platform", "globex").await.unwrap();
+
+        let context = "resources.credentials/r-1";
+        let acme = service.encrypt_field("acme", context, b"AKIA-acme-secret").await.unwrap();
+        let globex = service.encrypt_field("globex", context, b"AKIA-globex-secret").await.unwrap();
+        assert!(FieldCiphertext::is_ciphertext(&acme));
+        assert_eq!(*service.decrypt_field("acme", context, &acme).await.unwrap(), b"AKIA-acme-secret".to_vec());
+
+        // Neither asking as the other tenant nor relabelling the ciphertext opens it
+        assert!(matches!(service.decrypt_field("acme", context, &globex).await, Err(KeyVaultError::Permission(_))));
+        let mut relabelled: FieldCiphertext = globex.parse().unwrap();
+        relabelled.tenant = "acme".into();
+        assert!(matches!(service.decrypt_field("acme", context, &relabelled.to_string()).await, Err(KeyVaultError::Key(_))));
+        // Nor does the same tenant's ciphertext moved to another field
+        assert!(service.decrypt_field("acme", "resources.credentials/r-2", &acme).await.is_err());
+    }
+
+    // What the tenant does on its side of a BYOK import
+    fn wrap(params: &ImportParameters, material: &[u8]) -> Vec<u8> {
+        let public = PKey::public_key_from_pem(params.public_key_pem.as_bytes()).unwrap();
+        let mut ctx = PkeyCtx::new(&public).unwrap();
+        ctx.encrypt_init().unwrap();
+        ctx.set_rsa_padding(Padding::PKCS1_OAEP).unwrap();
+        ctx.set_rsa_oaep_md(Md::sha256()).unwrap();
+        ctx.set_rsa_mgf1_md(Md::sha256()).unwrap();
+        let mut wrapped = Vec::new();
+        ctx.encrypt_to_vec(material, &mut wrapped).unwrap();
+        wrapped
+    }
+
+    #[tokio::test]
+    async fn test_imported_key_and_shredding() {
+        let service = service().await;
+        let params = service.begin_import("svc-platform", "acme").await.unwrap();
+        let short = service
+            .import_tenant_key("svc-platform", "acme", &params.import_token, &wrap(&params, &[7u8; 16]))
+            .await;
+        assert!(matches!(short, Err(KeyVaultError::Validation(_))));
+        // The token was spent by the failed attempt
+        let reused = service
+            .import_tenant_key("svc-platform", "acme", &params.import_token, &wrap(&params, &[7u8; DATA_KEY_LEN]))
+            .await;
+        assert!(matches!(reused, Err(KeyVaultError::NotFound(_))));
+
+        let params = service.begin_import("svc-platform", "acme").await.unwrap();
+        let info = service
+            .import_tenant_key("svc-platform", "acme", &params.import_token, &wrap(&params, &[7u8; DATA_KEY_LEN]))
+            .await
+            .unwrap();
+        assert_eq!(info.versions[0].origin, TenantKeyOrigin::Imported);
+
+        let sealed = service.encrypt_field("acme", "audit_events.details/e-1", b"{\"ip\":\"10.0.0.1\"}").await.unwrap();
+        let challenge = service.request_revocation("security-admin", "acme").await.unwrap();
+        let wrong = service.confirm_revocation("security-admin", "acme", &challenge.token, "yes").await;
+        assert!(matches!(wrong, Err(KeyVaultError::Validation(_))));
+        let other = service.confirm_revocation("svc-platform", "acme", &challenge.token, &challenge.confirmation).await;
+        assert!(matches!(other, Err(KeyVaultError::Permission(_))));
+        let info = service
+            .confirm_revocation("security-admin", "acme", &challenge.token, &challenge.confirmation)
+            .await
+            .unwrap();
+        assert!(info.revoked_at.is_some());
+        assert!(info.versions.iter().all(|v| v.state == EncryptionKeyState::Revoked));
+
+        assert!(service.decrypt_field("acme", "audit_events.details/e-1", &sealed).await.is_err());
+        assert!(service.encrypt_field("acme", "audit_events.details/e-2", b"{}").await.is_err());
+        assert!(matches!(service.create_tenant_key("svc-platform", "acme").await, Err(KeyVaultError::Conflict(_))));
+    }
+}
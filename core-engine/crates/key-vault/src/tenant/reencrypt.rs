@@ -0,0 +1,352 @@
+//! Background re-encryption of a tenant's fields after its key is rotated. Jobs record a cursor
+//! per source after every batch, so a failed or interrupted job picks up where it stopped.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+use tracing::{error, info};
+
+use super::TenantKeyService;
+use crate::error::{KeyVaultError, KeyVaultResult};
+
+pub const DEFAULT_BATCH_SIZE: usize = 500;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EncryptedField {
+    pub row_id: String,
+    // The context the field was encrypted with
+    pub context: String,
+    pub ciphertext: String,
+}
+
+// A table (or other store) holding tenant-encrypted fields
+#[async_trait]
+pub trait EncryptedFieldSource: Send + Sync {
+    fn name(&self) -> &str;
+    // The tenant's fields ordered by row id, starting after `after`
+    async fn page(&self, tenant: &str, after: Option<&str>, limit: usize) -> KeyVaultResult<Vec<EncryptedField>>;
+    // Swaps the ciphertext only if the row still holds `current`, so a concurrent write isn't
+    // overwritten with older data; returns whether it was swapped
+    async fn replace(&self, tenant: &str, row_id: &str, current: &str, replacement: &str) -> KeyVaultResult<bool>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum ReencryptionStatus {
+    Running,
+    Completed,
+    Failed { error: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SourceProgress {
+    pub source: String,
+    // Last row id handled
+    pub cursor: Option<String>,
+    pub scanned: u64,
+    pub rewritten: u64,
+    pub done: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReencryptionJob {
+    pub id: String,
+    pub tenant: String,
+    pub target_version: u32,
+    pub status: ReencryptionStatus,
+    pub sources: Vec<SourceProgress>,
+    pub started_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub attempts: u32,
+}
+
+impl ReencryptionJob {
+    pub fn scanned(&self) -> u64 {
+        self.sources.iter().map(|s| s.scanned).sum()
+    }
+
+    pub fn rewritten(&self) -> u64 {
+        self.sources.iter().map(|s| s.rewritten).sum()
+    }
+}
+
+#[async_trait]
+pub trait ReencryptionJobStore: Send + Sync {
+    async fn save(&self, job: &ReencryptionJob) -> KeyVaultResult<()>;
+    async fn get(&self, id: &str) -> KeyVaultResult<ReencryptionJob>;
+    async fn list(&self, tenant: &str) -> KeyVaultResult<Vec<ReencryptionJob>>;
+}
+
+#[derive(Default)]
+pub struct InMemoryReencryptionJobStore {
+    jobs: RwLock<HashMap<String, ReencryptionJob>>,
+}
+
+impl InMemoryReencryptionJobStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl ReencryptionJobStore for InMemoryReencryptionJobStore {
+    async fn save(&self, job: &ReencryptionJob) -> KeyVaultResult<()> {
+        self.jobs.write().await.insert(job.id.clone(), job.clone());
+        Ok(())
+    }
+
+    async fn get(&self, id: &str) -> KeyVaultResult<ReencryptionJob> {
+        self.jobs
+            .read()
+            .await
+            .get(id)
+            .cloned()
+            .ok_or_else(|| KeyVaultError::NotFound(format!("Re-encryption job {} not found", id)))
+    }
+
+    async fn list(&self, tenant: &str) -> KeyVaultResult<Vec<ReencryptionJob>> {
+        let mut jobs: Vec<_> = self.jobs.read().await.values().filter(|j| j.tenant == tenant).cloned().collect();
+        jobs.sort_by_key(|j| j.started_at);
+        Ok(jobs)
+    }
+}
+
+pub struct Reencryptor {
+    keys: Arc<TenantKeyService>,
+    jobs: Arc<dyn ReencryptionJobStore>,
+    sources: Vec<Arc<dyn EncryptedFieldSource>>,
+    batch_size: usize,
+}
+
+impl Reencryptor {
+    pub fn new(keys: Arc<TenantKeyService>, jobs: Arc<dyn ReencryptionJobStore>) -> Self {
+        Self {
+            keys,
+            jobs,
+            sources: Vec::new(),
+            batch_size: DEFAULT_BATCH_SIZE,
+        }
+    }
+
+    pub fn with_source(mut self, source: Arc<dyn EncryptedFieldSource>) -> Self {
+        self.sources.push(source);
+        self
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    // Rotates the tenant's key and re-encrypts its fields in the background; poll `job` for progress
+    pub async fn rotate_tenant_key(self: &Arc<Self>, principal: &str, tenant: &str) -> KeyVaultResult<ReencryptionJob> {
+        let info = self.keys.rotate_key(principal, tenant).await?;
+        let job = self.start(tenant, info.current_version).await?;
+        self.spawn(&job.id);
+        Ok(job)
+    }
+
+    pub async fn start(&self, tenant: &str, target_version: u32) -> KeyVaultResult<ReencryptionJob> {
+        let now = Utc::now();
+        let job = ReencryptionJob {
+            id: uuid::Uuid::new_v4().to_string(),
+            tenant: tenant.to_string(),
+            target_version,
+            status: ReencryptionStatus::Running,
+            sources: self
+                .sources
+                .iter()
+                .map(|s| SourceProgress {
+                    source: s.name().to_string(),
+                    cursor: None,
+                    scanned: 0,
+                    rewritten: 0,
+                    done: false,
+                })
+                .collect(),
+            started_at: now,
+            updated_at: now,
+            attempts: 0,
+        };
+        self.jobs.save(&job).await?;
+        Ok(job)
+    }
+
+    pub fn spawn(self: &Arc<Self>, job_id: &str) -> JoinHandle<KeyVaultResult<ReencryptionJob>> {
+        let this = self.clone();
+        let job_id = job_id.to_string();
+        tokio::spawn(async move { this.run(&job_id).await })
+    }
+
+    pub async fn job(&self, id: &str) -> KeyVaultResult<ReencryptionJob> {
+        self.jobs.get(id).await
+    }
+
+    // Runs the job from its saved cursors; calling it again on a failed job resumes it
+    pub async fn run(&self, job_id: &str) -> KeyVaultResult<ReencryptionJob> {
+        let mut job = self.jobs.get(job_id).await?;
+        if job.status == ReencryptionStatus::Completed {
+            return Ok(job);
+        }
+        job.status = ReencryptionStatus::Running;
+        job.attempts += 1;
+        job.updated_at = Utc::now();
+        self.jobs.save(&job).await?;
+
+        job.status = match self.process(&mut job).await {
+            Ok(()) => {
+                info!(
+                    "Re-encrypted {} of {} fields of tenant {} (job {})",
+                    job.rewritten(),
+                    job.scanned(),
+                    job.tenant,
+                    job.id
+                );
+                ReencryptionStatus::Completed
+            }
+            Err(e) => {
+                error!("Re-encryption job {} for tenant {} failed: {}", job.id, job.tenant, e);
+                ReencryptionStatus::Failed { error: e.to_string() }
+            }
+        };
+        job.updated_at = Utc::now();
+        self.jobs.save(&job).await?;
+        Ok(job)
+    }
+
+    async fn process(&self, job: &mut ReencryptionJob) -> KeyVaultResult<()> {
+        for index in 0..job.sources.len() {
+            if job.sources[index].done {
+                continue;
+            }
+            let source = self
+                .sources
+                .iter()
+                .find(|s| s.name() == job.sources[index].source)
+                .ok_or_else(|| KeyVaultError::Config(format!("Unknown encrypted field source {}", job.sources[index].source)))?
+                .clone();
+            loop {
+                let page = source
+                    .page(&job.tenant, job.sources[index].cursor.as_deref(), self.batch_size)
+                    .await?;
+                for field in &page {
+                    if let Some(replacement) = self.keys.reencrypt_field(&job.tenant, &field.context, &field.ciphertext).await? {
+                        if source.replace(&job.tenant, &field.row_id, &field.ciphertext, &replacement).await? {
+                            job.sources[index].rewritten += 1;
+                        }
+                    }
+                }
+                let progress = &mut job.sources[index];
+                progress.scanned += page.len() as u64;
+                if let Some(last) = page.last() {
+                    progress.cursor = Some(last.row_id.clone());
+                }
+                progress.done = page.len() < self.batch_size;
+                job.updated_at = Utc::now();
+                self.jobs.save(job).await?;
+                if job.sources[index].done {
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::tenant::tests::service;
+    use crate::tenant::FieldCiphertext;
+
+    // Credentials table that fails the first time it's asked for its second page
+    struct FlakySource {
+        rows: Mutex<BTreeMap<String, (String, String)>>,
+        requested: Mutex<Vec<Option<String>>>,
+    }
+
+    #[async_trait]
+    impl EncryptedFieldSource for FlakySource {
+        fn name(&self) -> &str {
+            "resources.credentials"
+        }
+
+        async fn page(&self, _tenant: &str, after: Option<&str>, limit: usize) -> KeyVaultResult<Vec<EncryptedField>> {
+            let mut requested = self.requested.lock().unwrap();
+            requested.push(after.map(str::to_string));
+            if requested.len() == 2 {
+                return Err(KeyVaultError::Service("connection reset".into()));
+            }
+            Ok(self
+                .rows
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|(id, _)| after.map_or(true, |after| id.as_str() > after))
+                .take(limit)
+                .map(|(id, (context, ciphertext))| EncryptedField {
+                    row_id: id.clone(),
+                    context: context.clone(),
+                    ciphertext: ciphertext.clone(),
+                })
+                .collect())
+        }
+
+        async fn replace(&self, _tenant: &str, row_id: &str, current: &str, replacement: &str) -> KeyVaultResult<bool> {
+            let mut rows = self.rows.lock().unwrap();
+            match rows.get_mut(row_id) {
+                Some((_, ciphertext)) if ciphertext == current => {
+                    *ciphertext = replacement.to_string();
+                    Ok(true)
+                }
+                _ => Ok(false),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reencryption_resumes_from_cursor() {
+        let keys = Arc::new(service().await);
+        keys.create_tenant_key("svc-platform", "acme").await.unwrap();
+        let mut rows = BTreeMap::new();
+        for i in 0..5 {
+            let context = format!("resources.credentials/row-{}", i);
+            let ciphertext = keys.encrypt_field("acme", &context, format!("secret-{}", i).as_bytes()).await.unwrap();
+            rows.insert(format!("row-{}", i), (context, ciphertext));
+        }
+        let source = Arc::new(FlakySource { rows: Mutex::new(rows), requested: Mutex::new(Vec::new()) });
+        let reencryptor = Reencryptor::new(keys.clone(), Arc::new(InMemoryReencryptionJobStore::new()))
+            .with_source(source.clone())
+            .with_batch_size(2);
+
+        let info = keys.rotate_key("svc-platform", "acme").await.unwrap();
+        let job = reencryptor.start("acme", info.current_version).await.unwrap();
+        let failed = reencryptor.run(&job.id).await.unwrap();
+        assert!(matches!(failed.status, ReencryptionStatus::Failed { .. }));
+        assert_eq!(failed.sources[0].cursor.as_deref(), Some("row-1"));
+        assert_eq!(failed.rewritten(), 2);
+
+        let completed = reencryptor.run(&job.id).await.unwrap();
+        assert_eq!(completed.status, ReencryptionStatus::Completed);
+        assert_eq!(completed.attempts, 2);
+        assert_eq!((completed.scanned(), completed.rewritten()), (5, 5));
+        // The retry carried on from the saved cursor instead of starting over
+        assert_eq!(source.requested.lock().unwrap()[2].as_deref(), Some("row-1"));
+
+        let rows = source.rows.lock().unwrap().clone();
+        for (id, (context, ciphertext)) in &rows {
+            let parsed: FieldCiphertext = ciphertext.parse().unwrap();
+            assert_eq!(parsed.key_version, 2);
+            let plaintext = keys.decrypt_field("acme", context, ciphertext).await.unwrap();
+            assert_eq!(*plaintext, format!("secret-{}", &id[4..]).into_bytes());
+        }
+    }
+}
@@ -0,0 +1,263 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Arc;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use tokio::sync::Mutex;
+
+use crate::error::{KeyVaultError, KeyVaultResult};
+use crate::secret::{Secret, SecretManager, SecretValue};
+
+// KV data lives in `kv/<path>`; soft-delete state and custom metadata live in a sidecar `kv-meta/<path>`
+pub const DATA_PREFIX: &str = "kv/";
+pub const METADATA_PREFIX: &str = "kv-meta/";
+
+pub fn data_id(path: &str) -> String {
+    format!("{}{}", DATA_PREFIX, path)
+}
+
+fn metadata_id(path: &str) -> String {
+    format!("{}{}", METADATA_PREFIX, path)
+}
+
+pub fn vault_time(time: &DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Nanos, true)
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct KvMetadata {
+    custom_metadata: HashMap<String, String>,
+    deleted: BTreeMap<i32, DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone)]
+pub enum KvRead {
+    Found { data: Map<String, Value>, metadata: Value },
+    Deleted { metadata: Value },
+}
+
+pub struct KvStore {
+    secrets: Arc<dyn SecretManager>,
+    // Serializes read-modify-write sequences such as check-and-set and sidecar updates
+    write_lock: Mutex<()>,
+}
+
+impl KvStore {
+    pub fn new(secrets: Arc<dyn SecretManager>) -> Self {
+        Self {
+            secrets,
+            write_lock: Mutex::new(()),
+        }
+    }
+
+    async fn current(&self, path: &str) -> KeyVaultResult<Option<Secret>> {
+        match self.secrets.get_secret(&data_id(path)).await {
+            Ok(secret) => Ok(Some(secret)),
+            Err(KeyVaultError::NotFound(_)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn metadata(&self, path: &str) -> KeyVaultResult<KvMetadata> {
+        match self.secrets.get_secret(&metadata_id(path)).await {
+            Ok(Secret {
+                value: SecretValue::Plain(json),
+                ..
+            }) => serde_json::from_str(&json)
+                .map_err(|e| KeyVaultError::Internal(format!("Corrupt KV metadata for {}: {}", path, e))),
+            Ok(_) => Err(KeyVaultError::Internal(format!("Corrupt KV metadata for {}", path))),
+            Err(KeyVaultError::NotFound(_)) => Ok(KvMetadata::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn save_metadata(&self, path: &str, metadata: &KvMetadata) -> KeyVaultResult<()> {
+        let json = serde_json::to_string(metadata).map_err(|e| KeyVaultError::Internal(e.to_string()))?;
+        let secret = new_secret(metadata_id(path), SecretValue::Plain(json));
+        match self.secrets.update_secret(secret.clone()).await {
+            Err(KeyVaultError::NotFound(_)) => self.secrets.create_secret(secret).await.map(|_| ()),
+            other => other.map(|_| ()),
+        }
+    }
+
+    fn version_metadata(secret: &Secret, metadata: &KvMetadata) -> Value {
+        json!({
+            "created_time": vault_time(&secret.updated_at),
+            "custom_metadata": if metadata.custom_metadata.is_empty() { Value::Null } else { json!(metadata.custom_metadata) },
+            "deletion_time": metadata.deleted.get(&secret.version).map(vault_time).unwrap_or_default(),
+            "destroyed": false,
+            "version": secret.version,
+        })
+    }
+
+    pub async fn write(&self, path: &str, data: Map<String, Value>, cas: Option<i32>) -> KeyVaultResult<Value> {
+        let _guard = self.write_lock.lock().await;
+        let current = self.current(path).await?;
+        if let Some(cas) = cas {
+            let version = current.as_ref().map(|s| s.version).unwrap_or(0);
+            if cas != version {
+                return Err(KeyVaultError::Conflict(
+                    "check-and-set parameter did not match the current version".into(),
+                ));
+            }
+        }
+
+        let value = SecretValue::Plain(Value::Object(data).to_string());
+        let secret = new_secret(data_id(path), value);
+        let stored = match current {
+            Some(_) => self.secrets.update_secret(secret).await?,
+            None => self.secrets.create_secret(secret).await?,
+        };
+        Ok(Self::version_metadata(&stored, &self.metadata(path).await?))
+    }
+
+    pub async fn read(&self, path: &str, version: Option<i32>) -> KeyVaultResult<KvRead> {
+        let secret = match version {
+            Some(version) if version > 0 => self.secrets.get_secret_version(&data_id(path), version).await?,
+            _ => self.secrets.get_secret(&data_id(path)).await?,
+        };
+        let metadata = self.metadata(path).await?;
+        let version_metadata = Self::version_metadata(&secret, &metadata);
+        if metadata.deleted.contains_key(&secret.version) {
+            return Ok(KvRead::Deleted {
+                metadata: version_metadata,
+            });
+        }
+
+        let SecretValue::Plain(json) = &secret.value else {
+            return Err(KeyVaultError::Validation(format!("Secret {} is not a KV secret", path)));
+        };
+        let data = match serde_json::from_str(json) {
+            Ok(Value::Object(map)) => map,
+            // Secrets written outside the shim surface as a single `value` key
+            _ => Map::from_iter([("value".to_string(), Value::String(json.clone()))]),
+        };
+        Ok(KvRead::Found {
+            data,
+            metadata: version_metadata,
+        })
+    }
+
+    pub async fn delete_versions(&self, path: &str, versions: Option<Vec<i32>>) -> KeyVaultResult<()> {
+        let _guard = self.write_lock.lock().await;
+        let current = self
+            .current(path)
+            .await?
+            .ok_or_else(|| KeyVaultError::NotFound(format!("Secret {} not found", path)))?;
+        let mut metadata = self.metadata(path).await?;
+        let now = Utc::now();
+        for version in versions.unwrap_or_else(|| vec![current.version]) {
+            if version >= 1 && version <= current.version {
+                metadata.deleted.entry(version).or_insert(now);
+            }
+        }
+        self.save_metadata(path, &metadata).await
+    }
+
+    pub async fn undelete_versions(&self, path: &str, versions: Vec<i32>) -> KeyVaultResult<()> {
+        let _guard = self.write_lock.lock().await;
+        let mut metadata = self.metadata(path).await?;
+        for version in versions {
+            metadata.deleted.remove(&version);
+        }
+        self.save_metadata(path, &metadata).await
+    }
+
+    pub async fn read_metadata(&self, path: &str) -> KeyVaultResult<Value> {
+        let current = self
+            .current(path)
+            .await?
+            .ok_or_else(|| KeyVaultError::NotFound(format!("Secret {} not found", path)))?;
+        let metadata = self.metadata(path).await?;
+
+        let mut versions = Map::new();
+        let mut created_time = current.updated_at;
+        for version in 1..=current.version {
+            let secret = match self.secrets.get_secret_version(&data_id(path), version).await {
+                Ok(secret) => secret,
+                Err(KeyVaultError::NotFound(_)) => continue,
+                Err(e) => return Err(e),
+            };
+            created_time = created_time.min(secret.updated_at);
+            versions.insert(
+                version.to_string(),
+                json!({
+                    "created_time": vault_time(&secret.updated_at),
+                    "deletion_time": metadata.deleted.get(&version).map(vault_time).unwrap_or_default(),
+                    "destroyed": false,
+                }),
+            );
+        }
+        let oldest_version = versions.keys().filter_map(|v| v.parse::<i32>().ok()).min().unwrap_or(0);
+
+        Ok(json!({
+            "cas_required": false,
+            "created_time": vault_time(&created_time),
+            "current_version": current.version,
+            "custom_metadata": if metadata.custom_metadata.is_empty() { Value::Null } else { json!(metadata.custom_metadata) },
+            "delete_version_after": "0s",
+            "max_versions": 0,
+            "oldest_version": oldest_version,
+            "updated_time": vault_time(&current.updated_at),
+            "versions": versions,
+        }))
+    }
+
+    pub async fn write_metadata(&self, path: &str, custom_metadata: HashMap<String, String>) -> KeyVaultResult<()> {
+        let _guard = self.write_lock.lock().await;
+        let mut metadata = self.metadata(path).await?;
+        metadata.custom_metadata = custom_metadata;
+        self.save_metadata(path, &metadata).await
+    }
+
+    // Deletes every version and the metadata sidecar
+    pub async fn delete_metadata(&self, path: &str) -> KeyVaultResult<()> {
+        let _guard = self.write_lock.lock().await;
+        self.secrets.delete_secret(&data_id(path)).await?;
+        match self.secrets.delete_secret(&metadata_id(path)).await {
+            Err(KeyVaultError::NotFound(_)) => Ok(()),
+            other => other,
+        }
+    }
+
+    // Immediate children of `prefix`; nested paths collapse to `dir/` like Vault
+    pub async fn list(&self, prefix: &str) -> KeyVaultResult<Vec<String>> {
+        let full_prefix = data_id(prefix);
+        let keys: BTreeSet<String> = self
+            .secrets
+            .list_secrets()
+            .await?
+            .into_iter()
+            .filter_map(|secret| {
+                let rest = secret.id.strip_prefix(&full_prefix)?.to_string();
+                Some(match rest.split_once('/') {
+                    Some((dir, _)) => format!("{}/", dir),
+                    None => rest,
+                })
+            })
+            .filter(|key| !key.is_empty())
+            .collect();
+        if keys.is_empty() {
+            return Err(KeyVaultError::NotFound(format!("No secrets under {}", prefix)));
+        }
+        Ok(keys.into_iter().collect())
+    }
+}
+
+fn new_secret(id: String, value: SecretValue) -> Secret {
+    let now = Utc::now();
+    Secret {
+        name: id.clone(),
+        id,
+        description: None,
+        value,
+        version: 0,
+        created_at: now,
+        updated_at: now,
+        expires_at: None,
+        metadata: HashMap::from([("source".to_string(), "vault-kv-v2".to_string())]),
+        labels: HashMap::new(),
+        rotation_policy: None,
+    }
+}
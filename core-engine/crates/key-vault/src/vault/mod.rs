@@ -0,0 +1,397 @@
+//! HashiCorp Vault KV v2 compatibility shim.
+//!
+//! Serves the subset of the Vault HTTP API that KV v2 clients use, backed by a
+//! [`SecretManager`]. Vault tokens map to principals, and every request is
+//! checked against the [`AccessPolicyManager`] before it reaches the store.
+
+pub mod kv;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{HeaderMap, Method, StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use axum::routing::any;
+use axum::{Json, Router};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use tracing::debug;
+
+use crate::error::{KeyVaultError, KeyVaultResult};
+use crate::secret::{AccessPolicyManager, SecretAction, SecretManager};
+use kv::{data_id, KvRead, KvStore};
+
+pub const TOKEN_HEADER: &str = "X-Vault-Token";
+pub const DEFAULT_MOUNT: &str = "secret";
+
+#[derive(Debug)]
+pub struct VaultError {
+    status: StatusCode,
+    errors: Vec<String>,
+}
+
+impl VaultError {
+    fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            errors: vec![message.into()],
+        }
+    }
+
+    fn permission_denied() -> Self {
+        Self::new(StatusCode::FORBIDDEN, "permission denied")
+    }
+
+    fn unsupported_path(path: &str) -> Self {
+        Self::new(StatusCode::NOT_FOUND, format!("1 error occurred:\n\t* unsupported path: {}\n\n", path))
+    }
+
+    fn unsupported_operation() -> Self {
+        Self::new(StatusCode::METHOD_NOT_ALLOWED, "1 error occurred:\n\t* unsupported operation\n\n")
+    }
+}
+
+// Vault reports missing secrets as a bare 404 with an empty error list
+impl From<KeyVaultError> for VaultError {
+    fn from(error: KeyVaultError) -> Self {
+        match error {
+            KeyVaultError::NotFound(_) => Self {
+                status: StatusCode::NOT_FOUND,
+                errors: Vec::new(),
+            },
+            KeyVaultError::Permission(_) => Self::permission_denied(),
+            KeyVaultError::Validation(msg) | KeyVaultError::Conflict(msg) => Self::new(StatusCode::BAD_REQUEST, msg),
+            other => Self::new(StatusCode::INTERNAL_SERVER_ERROR, other.to_string()),
+        }
+    }
+}
+
+impl IntoResponse for VaultError {
+    fn into_response(self) -> Response {
+        (self.status, Json(json!({ "errors": self.errors }))).into_response()
+    }
+}
+
+type VaultResponse = Result<Response, VaultError>;
+
+fn envelope(data: Value) -> Value {
+    json!({
+        "request_id": uuid::Uuid::new_v4().to_string(),
+        "lease_id": "",
+        "renewable": false,
+        "lease_duration": 0,
+        "data": data,
+        "wrap_info": null,
+        "warnings": null,
+        "auth": null,
+    })
+}
+
+fn ok(data: Value) -> VaultResponse {
+    Ok(Json(envelope(data)).into_response())
+}
+
+fn no_content() -> VaultResponse {
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct WriteOptions {
+    cas: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WriteRequest {
+    data: Map<String, Value>,
+    #[serde(default)]
+    options: WriteOptions,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct VersionsRequest {
+    #[serde(default)]
+    versions: Vec<i32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct MetadataRequest {
+    #[serde(default)]
+    custom_metadata: HashMap<String, String>,
+}
+
+fn parse_body<T: for<'de> Deserialize<'de> + Default>(body: &Bytes) -> Result<T, VaultError> {
+    if body.is_empty() {
+        return Ok(T::default());
+    }
+    serde_json::from_slice(body)
+        .map_err(|e| VaultError::new(StatusCode::BAD_REQUEST, format!("failed to parse JSON input: {}", e)))
+}
+
+fn query_param(uri: &Uri, name: &str) -> Option<String> {
+    uri.query()?.split('&').find_map(|pair| {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        (key == name).then(|| value.to_string())
+    })
+}
+
+pub struct VaultShim {
+    kv: KvStore,
+    policies: Arc<dyn AccessPolicyManager>,
+    mount: String,
+    tokens: HashMap<String, String>,
+}
+
+impl VaultShim {
+    pub fn new(secrets: Arc<dyn SecretManager>, policies: Arc<dyn AccessPolicyManager>) -> Self {
+        Self {
+            kv: KvStore::new(secrets),
+            policies,
+            mount: DEFAULT_MOUNT.to_string(),
+            tokens: HashMap::new(),
+        }
+    }
+
+    pub fn with_mount(mut self, mount: impl Into<String>) -> Self {
+        self.mount = mount.into();
+        self
+    }
+
+    // Requests carrying `token` are evaluated as `principal` against the access policies
+    pub fn with_token(mut self, token: impl Into<String>, principal: impl Into<String>) -> Self {
+        self.tokens.insert(token.into(), principal.into());
+        self
+    }
+
+    pub fn router(self) -> Router {
+        Router::new()
+            .route("/v1/*path", any(handle))
+            .with_state(Arc::new(self))
+    }
+
+    async fn authorize(&self, headers: &HeaderMap, path: &str, action: SecretAction) -> Result<(), VaultError> {
+        let principal = headers
+            .get(TOKEN_HEADER)
+            .and_then(|token| token.to_str().ok())
+            .and_then(|token| self.tokens.get(token))
+            .ok_or_else(VaultError::permission_denied)?;
+        if self.policies.validate_access(principal, &data_id(path), action).await? {
+            Ok(())
+        } else {
+            debug!("Vault shim denied {:?} on {} for {}", action, path, principal);
+            Err(VaultError::permission_denied())
+        }
+    }
+
+    async fn dispatch(&self, method: Method, uri: &Uri, headers: &HeaderMap, body: Bytes) -> VaultResponse {
+        let full_path = uri.path().trim_start_matches("/v1/");
+        let unsupported = || VaultError::unsupported_path(full_path);
+
+        let (mount, rest) = full_path.split_once('/').ok_or_else(unsupported)?;
+        if mount != self.mount {
+            return Err(unsupported());
+        }
+        let (operation, path) = rest.split_once('/').unwrap_or((rest, ""));
+        let listing = method.as_str() == "LIST" || (method == Method::GET && query_param(uri, "list").as_deref() == Some("true"));
+
+        match (operation, method) {
+            ("metadata", _) if listing => {
+                let prefix = match path.trim_end_matches('/') {
+                    "" => String::new(),
+                    dir => format!("{}/", dir),
+                };
+                self.authorize(headers, &prefix, SecretAction::List).await?;
+                ok(json!({ "keys": self.kv.list(&prefix).await? }))
+            }
+            (_, _) if path.is_empty() => Err(unsupported()),
+            ("data", Method::GET) => {
+                self.authorize(headers, path, SecretAction::Read).await?;
+                let version = query_param(uri, "version")
+                    .map(|v| v.parse::<i32>())
+                    .transpose()
+                    .map_err(|_| VaultError::new(StatusCode::BAD_REQUEST, "invalid version"))?;
+                match self.kv.read(path, version).await? {
+                    KvRead::Found { data, metadata } => ok(json!({ "data": data, "metadata": metadata })),
+                    // Soft-deleted versions answer 404 but still describe themselves
+                    KvRead::Deleted { metadata } => Ok((
+                        StatusCode::NOT_FOUND,
+                        Json(envelope(json!({ "data": null, "metadata": metadata }))),
+                    )
+                        .into_response()),
+                }
+            }
+            ("data", Method::POST | Method::PUT) => {
+                self.authorize(headers, path, SecretAction::Write).await?;
+                let request: WriteRequest = serde_json::from_slice(&body).map_err(|_| {
+                    VaultError::new(StatusCode::BAD_REQUEST, "no data provided")
+                })?;
+                ok(self.kv.write(path, request.data, request.options.cas).await?)
+            }
+            ("data", Method::DELETE) => {
+                self.authorize(headers, path, SecretAction::Delete).await?;
+                self.kv.delete_versions(path, None).await?;
+                no_content()
+            }
+            ("delete", Method::POST | Method::PUT) => {
+                self.authorize(headers, path, SecretAction::Delete).await?;
+                let request: VersionsRequest = parse_body(&body)?;
+                self.kv.delete_versions(path, Some(request.versions)).await?;
+                no_content()
+            }
+            ("undelete", Method::POST | Method::PUT) => {
+                self.authorize(headers, path, SecretAction::Write).await?;
+                let request: VersionsRequest = parse_body(&body)?;
+                self.kv.undelete_versions(path, request.versions).await?;
+                no_content()
+            }
+            ("metadata", Method::GET) => {
+                self.authorize(headers, path, SecretAction::Read).await?;
+                ok(self.kv.read_metadata(path).await?)
+            }
+            ("metadata", Method::POST | Method::PUT) => {
+                self.authorize(headers, path, SecretAction::Write).await?;
+                let request: MetadataRequest = parse_body(&body)?;
+                self.kv.write_metadata(path, request.custom_metadata).await?;
+                no_content()
+            }
+            ("metadata", Method::DELETE) => {
+                self.authorize(headers, path, SecretAction::Delete).await?;
+                self.kv.delete_metadata(path).await?;
+                no_content()
+            }
+            ("data" | "delete" | "undelete" | "metadata", _) => Err(VaultError::unsupported_operation()),
+            // Destroy, config and subkeys have no SecretManager equivalent
+            _ => Err(unsupported()),
+        }
+    }
+}
+
+async fn handle(
+    State(shim): State<Arc<VaultShim>>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> VaultResponse {
+    shim.dispatch(method, &uri, &headers, body).await
+}
+
+// Serves the shim on an already bound listener until the server fails
+pub async fn serve(shim: VaultShim, listener: std::net::TcpListener) -> KeyVaultResult<()> {
+    axum::Server::from_tcp(listener)
+        .map_err(|e| KeyVaultError::Service(e.to_string()))?
+        .serve(shim.router().into_make_service())
+        .await
+        .map_err(|e| KeyVaultError::Service(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secret::{AccessPolicy, InMemoryAccessPolicyManager, InMemorySecretManager, SecretPermission};
+    use vaultrs::client::{VaultClient, VaultClientSettingsBuilder};
+    use vaultrs::error::ClientError;
+    use vaultrs::kv2;
+
+    async fn start_shim() -> String {
+        let policies = InMemoryAccessPolicyManager::new();
+        policies
+            .create_policy(AccessPolicy {
+                id: "app".into(),
+                name: "app".into(),
+                description: None,
+                principals: vec!["svc-app".into()],
+                permissions: vec![SecretPermission {
+                    actions: vec![
+                        SecretAction::Read,
+                        SecretAction::Write,
+                        SecretAction::Delete,
+                        SecretAction::List,
+                    ],
+                    secret_patterns: vec!["kv/app/*".into()],
+                }],
+                conditions: None,
+            })
+            .await
+            .unwrap();
+
+        let shim = VaultShim::new(Arc::new(InMemorySecretManager::new()), Arc::new(policies))
+            .with_token("app-token", "svc-app")
+            .with_token("other-token", "svc-other");
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(serve(shim, listener));
+        address
+    }
+
+    fn client(address: &str, token: &str) -> VaultClient {
+        VaultClient::new(
+            VaultClientSettingsBuilder::default()
+                .address(address)
+                .token(token)
+                .build()
+                .unwrap(),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_vaultrs_reads_and_writes_versions() {
+        let address = start_shim().await;
+        let client = client(&address, "app-token");
+
+        let first = kv2::set(&client, "secret", "app/db", &HashMap::from([("password", "one")]))
+            .await
+            .unwrap();
+        assert_eq!(first.version, 1);
+        kv2::set(&client, "secret", "app/db", &HashMap::from([("password", "two")]))
+            .await
+            .unwrap();
+
+        let latest: HashMap<String, String> = kv2::read(&client, "secret", "app/db").await.unwrap();
+        assert_eq!(latest["password"], "two");
+        let original: HashMap<String, String> = kv2::read_version(&client, "secret", "app/db", 1).await.unwrap();
+        assert_eq!(original["password"], "one");
+
+        kv2::delete_latest(&client, "secret", "app/db").await.unwrap();
+        assert!(kv2::read::<HashMap<String, String>>(&client, "secret", "app/db").await.is_err());
+        let metadata = kv2::read_metadata(&client, "secret", "app/db").await.unwrap();
+        assert_eq!(metadata.current_version, 2);
+        assert!(!metadata.versions["2"].deletion_time.is_empty());
+        assert!(metadata.versions["1"].deletion_time.is_empty());
+
+        kv2::undelete_versions(&client, "secret", "app/db", vec![2]).await.unwrap();
+        let restored: HashMap<String, String> = kv2::read(&client, "secret", "app/db").await.unwrap();
+        assert_eq!(restored["password"], "two");
+
+        kv2::set(&client, "secret", "app/nested/key", &HashMap::from([("k", "v")]))
+            .await
+            .unwrap();
+        let keys = kv2::list(&client, "secret", "app").await.unwrap();
+        assert_eq!(keys, vec!["db".to_string(), "nested/".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_denied_token_and_unsupported_endpoint() {
+        let address = start_shim().await;
+
+        let other = client(&address, "other-token");
+        let denied = kv2::read::<HashMap<String, String>>(&other, "secret", "app/db").await;
+        assert!(matches!(denied, Err(ClientError::APIError { code: 403, .. })));
+
+        let unknown = client(&address, "unknown-token");
+        let denied = kv2::set(&unknown, "secret", "app/db", &HashMap::from([("k", "v")])).await;
+        assert!(matches!(denied, Err(ClientError::APIError { code: 403, .. })));
+
+        let app = client(&address, "app-token");
+        let destroy = kv2::destroy_versions(&app, "secret", "app/db", vec![1]).await;
+        match destroy {
+            Err(ClientError::APIError { code, errors }) => {
+                assert_eq!(code, 404);
+                assert!(errors[0].contains("unsupported path"));
+            }
+            other => panic!("expected unsupported path error, got {:?}", other),
+        }
+    }
+}
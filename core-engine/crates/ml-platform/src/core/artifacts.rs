@@ -0,0 +1,62 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use sirsi_object_store::{ByteStream, ObjectStore, PutOptions, StoreResult, Uploader};
+
+use super::{ArtifactType, ModelArtifact};
+
+// Model artifacts in object storage under `<prefix>/<model>/<artifact>`; the checksum recorded on
+// the artifact is the store's, and reads through `open` are verified against it
+pub struct ArtifactStore {
+    store: Arc<dyn ObjectStore>,
+    prefix: String,
+}
+
+impl ArtifactStore {
+    pub fn new(store: Arc<dyn ObjectStore>) -> Self {
+        Self { store, prefix: "models".into() }
+    }
+
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    fn key(&self, model_id: &str, artifact_id: &str) -> String {
+        format!("{}/{}/{}", self.prefix, model_id, artifact_id)
+    }
+
+    pub async fn save(
+        &self,
+        model_id: &str,
+        artifact_id: &str,
+        artifact_type: ArtifactType,
+        path: &Path,
+        metadata: HashMap<String, String>,
+    ) -> StoreResult<ModelArtifact> {
+        let key = self.key(model_id, artifact_id);
+        let options = PutOptions {
+            content_type: Some("application/octet-stream".into()),
+            metadata: metadata.clone(),
+        };
+        let meta = Uploader::new(self.store.clone()).upload_file(&key, path, &options).await?;
+        Ok(ModelArtifact {
+            id: artifact_id.to_string(),
+            artifact_type,
+            uri: self.store.url(&key),
+            size_bytes: meta.size,
+            checksum: meta.checksum.map(|c| c.to_string()).unwrap_or_default(),
+            metadata,
+        })
+    }
+
+    pub async fn open(&self, model_id: &str, artifact_id: &str) -> StoreResult<ByteStream> {
+        let (_, stream) = self.store.get(&self.key(model_id, artifact_id)).await?;
+        Ok(stream)
+    }
+
+    pub async fn delete(&self, model_id: &str, artifact_id: &str) -> StoreResult<()> {
+        self.store.delete(&self.key(model_id, artifact_id)).await
+    }
+}
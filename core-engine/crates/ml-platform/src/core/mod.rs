@@ -5,6 +5,8 @@ use chrono::{DateTime, Utc};
 
 use crate::error::MLResult;
 
+pub mod artifacts;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Model {
     pub id: String,
@@ -0,0 +1,523 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+use maxminddb::{geoip2, MaxMindDBError, Reader};
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::error::{NetworkError, NetworkResult};
+use super::{RecordSet, RoutingPolicy};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GeoDnsConfig {
+    pub database_path: PathBuf,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GeoLocation {
+    pub continent: Option<String>,
+    pub country: Option<String>,
+    pub subdivision: Option<String>,
+}
+
+// EDNS Client Subnet option (RFC 7871) as received on the query
+#[derive(Debug, Clone, Copy)]
+pub struct ClientSubnet {
+    pub address: IpAddr,
+    pub source_prefix: u8,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct QueryClient {
+    pub source_ip: IpAddr,
+    pub client_subnet: Option<ClientSubnet>,
+}
+
+impl QueryClient {
+    pub fn new(source_ip: IpAddr) -> Self {
+        Self {
+            source_ip,
+            client_subnet: None,
+        }
+    }
+
+    pub fn with_client_subnet(mut self, address: IpAddr, source_prefix: u8) -> Self {
+        self.client_subnet = Some(ClientSubnet { address, source_prefix });
+        self
+    }
+
+    // The subnet's network address stands in for the client when ECS is present
+    pub fn geo_address(&self) -> IpAddr {
+        let Some(subnet) = self.client_subnet else {
+            return self.source_ip;
+        };
+        match subnet.address {
+            IpAddr::V4(ip) => {
+                let prefix = subnet.source_prefix.min(32) as u32;
+                let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+                IpAddr::V4((u32::from(ip) & mask).into())
+            }
+            IpAddr::V6(ip) => {
+                let prefix = subnet.source_prefix.min(128) as u32;
+                let mask = u128::MAX.checked_shl(128 - prefix).unwrap_or(0);
+                IpAddr::V6((u128::from(ip) & mask).into())
+            }
+        }
+    }
+}
+
+pub struct GeoDatabase {
+    reader: Reader<Vec<u8>>,
+}
+
+impl GeoDatabase {
+    pub fn open(path: &std::path::Path) -> NetworkResult<Self> {
+        let reader = Reader::open_readfile(path)
+            .map_err(|e| NetworkError::Config(format!("Failed to open geo database {}: {}", path.display(), e)))?;
+        Ok(Self { reader })
+    }
+
+    pub fn locate(&self, ip: IpAddr) -> NetworkResult<Option<GeoLocation>> {
+        let city: geoip2::City = match self.reader.lookup(ip) {
+            Ok(city) => city,
+            Err(MaxMindDBError::AddressNotFoundError(_)) => return Ok(None),
+            Err(e) => return Err(NetworkError::Dns(format!("Geo lookup for {} failed: {}", ip, e))),
+        };
+        let location = GeoLocation {
+            continent: city.continent.and_then(|c| c.code).map(str::to_string),
+            country: city.country.and_then(|c| c.iso_code).map(str::to_string),
+            subdivision: city
+                .subdivisions
+                .and_then(|s| s.into_iter().next())
+                .and_then(|s| s.iso_code)
+                .map(str::to_string),
+        };
+        Ok((location != GeoLocation::default()).then_some(location))
+    }
+}
+
+// Specificity of a geolocation record: subdivision > country > continent > default
+fn specificity(
+    continent: &Option<String>,
+    country: &Option<String>,
+    subdivision: &Option<String>,
+    location: Option<&GeoLocation>,
+) -> Option<u8> {
+    let field_matches = |want: &Option<String>, have: Option<&Option<String>>| match want {
+        None => true,
+        Some(want) => have.and_then(|h| h.as_deref()).is_some_and(|h| h.eq_ignore_ascii_case(want)),
+    };
+    let matches = field_matches(continent, location.map(|l| &l.continent))
+        && field_matches(country, location.map(|l| &l.country))
+        && field_matches(subdivision, location.map(|l| &l.subdivision));
+    if !matches {
+        return None;
+    }
+    Some(match (continent, country, subdivision) {
+        (_, _, Some(_)) => 3,
+        (_, Some(_), None) => 2,
+        (Some(_), None, None) => 1,
+        (None, None, None) => 0,
+    })
+}
+
+// Picks the most specific geolocation record set for `location`, falling back to the default
+// (all fields unset) record set. Record sets with other routing policies are ignored.
+pub fn select_geolocation<'a>(candidates: &'a [RecordSet], location: Option<&GeoLocation>) -> Option<(&'a RecordSet, String)> {
+    candidates
+        .iter()
+        .filter_map(|record| match &record.routing_policy {
+            Some(RoutingPolicy::Geolocation {
+                continent,
+                country,
+                subdivision,
+            }) => specificity(continent, country, subdivision, location).map(|score| (score, record)),
+            _ => None,
+        })
+        .max_by_key(|(score, _)| *score)
+        .map(|(_, record)| (record, match_label(record)))
+}
+
+fn match_label(record: &RecordSet) -> String {
+    match &record.routing_policy {
+        Some(RoutingPolicy::Geolocation {
+            country: Some(country),
+            subdivision: Some(subdivision),
+            ..
+        }) => format!("subdivision:{}-{}", country, subdivision),
+        Some(RoutingPolicy::Geolocation {
+            country: Some(country), ..
+        }) => format!("country:{}", country),
+        Some(RoutingPolicy::Geolocation {
+            continent: Some(continent),
+            ..
+        }) => format!("continent:{}", continent),
+        _ => "default".to_string(),
+    }
+}
+
+pub const NO_MATCH_LABEL: &str = "no_match";
+
+// Geolocation answer selection for the resolver. The database can be swapped at runtime.
+pub struct GeoRouter {
+    config: GeoDnsConfig,
+    database: RwLock<Option<(Arc<GeoDatabase>, Option<SystemTime>)>>,
+    matches: RwLock<HashMap<String, AtomicU64>>,
+}
+
+impl GeoRouter {
+    // A missing or unreadable database is not fatal: every query gets the default answer until
+    // a reload succeeds
+    pub fn new(config: GeoDnsConfig) -> Self {
+        let router = Self {
+            config,
+            database: RwLock::new(None),
+            matches: RwLock::new(HashMap::new()),
+        };
+        if let Err(e) = router.reload() {
+            warn!("Geo DNS starting without a database: {}", e);
+        }
+        router
+    }
+
+    fn modified(&self) -> Option<SystemTime> {
+        std::fs::metadata(&self.config.database_path).and_then(|m| m.modified()).ok()
+    }
+
+    // Keeps serving the previous database if the new one fails to load
+    pub fn reload(&self) -> NetworkResult<()> {
+        let modified = self.modified();
+        let database = GeoDatabase::open(&self.config.database_path)?;
+        *self.database.write().expect("geo database lock poisoned") = Some((Arc::new(database), modified));
+        info!("Loaded geo database {}", self.config.database_path.display());
+        Ok(())
+    }
+
+    pub fn reload_if_changed(&self) -> NetworkResult<bool> {
+        let loaded = self
+            .database
+            .read()
+            .expect("geo database lock poisoned")
+            .as_ref()
+            .and_then(|(_, modified)| *modified);
+        if loaded.is_some() && loaded == self.modified() {
+            return Ok(false);
+        }
+        self.reload().map(|_| true)
+    }
+
+    pub fn spawn_reloader(self: Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = self.reload_if_changed() {
+                    warn!("Geo database reload failed: {}", e);
+                }
+            }
+        })
+    }
+
+    pub fn locate(&self, client: &QueryClient) -> Option<GeoLocation> {
+        let database = self
+            .database
+            .read()
+            .expect("geo database lock poisoned")
+            .as_ref()
+            .map(|(db, _)| db.clone())?;
+        let address = client.geo_address();
+        database.locate(address).unwrap_or_else(|e| {
+            warn!("{}", e);
+            None
+        })
+    }
+
+    pub fn select<'a>(&self, candidates: &'a [RecordSet], client: &QueryClient) -> Option<&'a RecordSet> {
+        let location = self.locate(client);
+        let selected = select_geolocation(candidates, location.as_ref());
+        self.count(selected.as_ref().map(|(_, label)| label.as_str()).unwrap_or(NO_MATCH_LABEL));
+        selected.map(|(record, _)| record)
+    }
+
+    fn count(&self, label: &str) {
+        if let Some(counter) = self.matches.read().expect("geo metrics lock poisoned").get(label) {
+            counter.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        self.matches
+            .write()
+            .expect("geo metrics lock poisoned")
+            .entry(label.to_string())
+            .or_default()
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn match_counts(&self) -> HashMap<String, u64> {
+        self.matches
+            .read()
+            .expect("geo metrics lock poisoned")
+            .iter()
+            .map(|(label, count)| (label.clone(), count.load(Ordering::Relaxed)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns::RecordType;
+
+    // Minimal MaxMind DB writer covering the handful of types a City record needs
+    mod mmdb {
+        pub enum Value {
+            Str(&'static str),
+            U16(u16),
+            U32(u32),
+            U64(u64),
+            Array(Vec<Value>),
+            Map(Vec<(&'static str, Value)>),
+        }
+
+        fn control(out: &mut Vec<u8>, kind: u8, size: usize) {
+            let (first, extended) = if kind <= 7 { (kind << 5, None) } else { (0, Some(kind - 7)) };
+            let (size_bits, size_bytes): (u8, Vec<u8>) = match size {
+                0..=28 => (size as u8, vec![]),
+                29..=284 => (29, vec![(size - 29) as u8]),
+                _ => (30, ((size - 285) as u16).to_be_bytes().to_vec()),
+            };
+            out.push(first | size_bits);
+            out.extend(extended);
+            out.extend(size_bytes);
+        }
+
+        fn unsigned(out: &mut Vec<u8>, kind: u8, value: u64) {
+            let bytes: Vec<u8> = value.to_be_bytes().into_iter().skip_while(|b| *b == 0).collect();
+            control(out, kind, bytes.len());
+            out.extend(bytes);
+        }
+
+        pub fn encode(out: &mut Vec<u8>, value: &Value) {
+            match value {
+                Value::Str(s) => {
+                    control(out, 2, s.len());
+                    out.extend(s.as_bytes());
+                }
+                Value::U16(v) => unsigned(out, 5, *v as u64),
+                Value::U32(v) => unsigned(out, 6, *v as u64),
+                Value::U64(v) => unsigned(out, 9, *v),
+                Value::Array(items) => {
+                    control(out, 11, items.len());
+                    items.iter().for_each(|item| encode(out, item));
+                }
+                Value::Map(pairs) => {
+                    control(out, 7, pairs.len());
+                    for (key, value) in pairs {
+                        encode(out, &Value::Str(key));
+                        encode(out, value);
+                    }
+                }
+            }
+        }
+
+        #[derive(Clone, Copy)]
+        enum Record {
+            Empty,
+            Node(usize),
+            Data(usize),
+        }
+
+        // IPv4 database with 24-bit records; prefixes must not overlap
+        pub fn build(entries: Vec<(&str, Value)>) -> Vec<u8> {
+            let mut nodes: Vec<[Record; 2]> = vec![[Record::Empty; 2]];
+            let mut data = Vec::new();
+            for (cidr, value) in entries {
+                let (ip, prefix) = cidr.split_once('/').unwrap();
+                let bits = u32::from(ip.parse::<std::net::Ipv4Addr>().unwrap());
+                let prefix: usize = prefix.parse().unwrap();
+                let offset = data.len();
+                encode(&mut data, &value);
+
+                let mut node = 0;
+                for depth in 0..prefix {
+                    let bit = ((bits >> (31 - depth)) & 1) as usize;
+                    if depth == prefix - 1 {
+                        nodes[node][bit] = Record::Data(offset);
+                    } else {
+                        node = match nodes[node][bit] {
+                            Record::Node(next) => next,
+                            _ => {
+                                nodes.push([Record::Empty; 2]);
+                                nodes[node][bit] = Record::Node(nodes.len() - 1);
+                                nodes.len() - 1
+                            }
+                        };
+                    }
+                }
+            }
+
+            let node_count = nodes.len();
+            let mut out = Vec::new();
+            for node in &nodes {
+                for record in node {
+                    let value = match record {
+                        Record::Empty => node_count,
+                        Record::Node(n) => *n,
+                        Record::Data(offset) => node_count + 16 + offset,
+                    };
+                    out.extend(&(value as u32).to_be_bytes()[1..]);
+                }
+            }
+            out.extend([0u8; 16]);
+            out.extend(data);
+            out.extend(b"\xAB\xCD\xEFMaxMind.com");
+            encode(
+                &mut out,
+                &Value::Map(vec![
+                    ("binary_format_major_version", Value::U16(2)),
+                    ("binary_format_minor_version", Value::U16(0)),
+                    ("build_epoch", Value::U64(1_700_000_000)),
+                    ("database_type", Value::Str("SirsiNexus-Test-City")),
+                    ("description", Value::Map(vec![("en", Value::Str("GeoDNS test fixture"))])),
+                    ("ip_version", Value::U16(4)),
+                    ("languages", Value::Array(vec![Value::Str("en")])),
+                    ("node_count", Value::U32(node_count as u32)),
+                    ("record_size", Value::U16(24)),
+                ]),
+            );
+            out
+        }
+
+        pub fn city(continent: &'static str, country: &'static str, subdivision: Option<&'static str>) -> Value {
+            let mut fields = vec![
+                ("continent", Value::Map(vec![("code", Value::Str(continent))])),
+                ("country", Value::Map(vec![("iso_code", Value::Str(country))])),
+            ];
+            if let Some(subdivision) = subdivision {
+                fields.push((
+                    "subdivisions",
+                    Value::Array(vec![Value::Map(vec![("iso_code", Value::Str(subdivision))])]),
+                ));
+            }
+            Value::Map(fields)
+        }
+    }
+
+    fn fixture(tokyo_as_california: bool) -> Vec<u8> {
+        let tokyo = if tokyo_as_california {
+            mmdb::city("NA", "US", Some("CA"))
+        } else {
+            mmdb::city("AS", "JP", Some("13"))
+        };
+        mmdb::build(vec![
+            ("10.1.0.0/16", mmdb::city("NA", "US", Some("CA"))),
+            ("10.2.0.0/16", mmdb::city("NA", "US", Some("TX"))),
+            ("10.3.0.0/16", mmdb::city("EU", "DE", None)),
+            ("10.4.0.0/16", tokyo),
+            ("10.5.0.0/16", mmdb::city("NA", "MX", None)),
+        ])
+    }
+
+    fn fixture_path() -> PathBuf {
+        std::env::temp_dir().join(format!("sirsi-geo-{}.mmdb", uuid::Uuid::new_v4()))
+    }
+
+    fn geo_record(id: &str, continent: Option<&str>, country: Option<&str>, subdivision: Option<&str>) -> RecordSet {
+        RecordSet {
+            id: id.into(),
+            zone_id: "zone-1".into(),
+            name: "api.example.com".into(),
+            record_type: RecordType::A,
+            ttl: 60,
+            records: vec![],
+            routing_policy: Some(RoutingPolicy::Geolocation {
+                continent: continent.map(str::to_string),
+                country: country.map(str::to_string),
+                subdivision: subdivision.map(str::to_string),
+            }),
+            health_check: None,
+            alias_target: None,
+        }
+    }
+
+    fn records() -> Vec<RecordSet> {
+        vec![
+            geo_record("default", None, None, None),
+            geo_record("north-america", Some("NA"), None, None),
+            geo_record("europe", Some("EU"), None, None),
+            geo_record("us", None, Some("US"), None),
+            geo_record("us-ca", None, Some("US"), Some("CA")),
+        ]
+    }
+
+    fn selected(router: &GeoRouter, records: &[RecordSet], client: QueryClient) -> String {
+        router.select(records, &client).map(|r| r.id.clone()).unwrap()
+    }
+
+    #[test]
+    fn test_specificity_ordering_and_fallback() {
+        let path = fixture_path();
+        std::fs::write(&path, fixture(false)).unwrap();
+        let router = GeoRouter::new(GeoDnsConfig { database_path: path.clone() });
+        let records = records();
+        let client = |ip: &str| QueryClient::new(ip.parse().unwrap());
+
+        assert_eq!(selected(&router, &records, client("10.1.4.4")), "us-ca");
+        assert_eq!(selected(&router, &records, client("10.2.4.4")), "us");
+        assert_eq!(selected(&router, &records, client("10.5.4.4")), "north-america");
+        assert_eq!(selected(&router, &records, client("10.3.4.4")), "europe");
+        assert_eq!(selected(&router, &records, client("10.4.4.4")), "default");
+        assert_eq!(selected(&router, &records, client("192.168.1.1")), "default");
+
+        let without_default: Vec<RecordSet> = records.into_iter().filter(|r| r.id != "default").collect();
+        assert!(router.select(&without_default, &client("10.4.4.4")).is_none());
+
+        let counts = router.match_counts();
+        assert_eq!(counts["subdivision:US-CA"], 1);
+        assert_eq!(counts["country:US"], 1);
+        assert_eq!(counts["continent:NA"], 1);
+        assert_eq!(counts["default"], 2);
+        assert_eq!(counts[NO_MATCH_LABEL], 1);
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_client_subnet_and_reload() {
+        let path = fixture_path();
+        std::fs::write(&path, fixture(false)).unwrap();
+        let router = GeoRouter::new(GeoDnsConfig { database_path: path.clone() });
+        let records = records();
+
+        // The resolver's own address is in Tokyo, but ECS says the client is in California
+        let resolver = "10.4.0.53".parse().unwrap();
+        let ecs = QueryClient::new(resolver).with_client_subnet("10.1.77.0".parse().unwrap(), 24);
+        assert_eq!(ecs.geo_address(), "10.1.77.0".parse::<IpAddr>().unwrap());
+        assert_eq!(selected(&router, &records, ecs), "us-ca");
+        assert_eq!(selected(&router, &records, QueryClient::new(resolver)), "default");
+
+        std::fs::write(&path, fixture(true)).unwrap();
+        router.reload().unwrap();
+        assert_eq!(selected(&router, &records, QueryClient::new(resolver)), "us-ca");
+
+        // A corrupt replacement leaves the previous database serving
+        std::fs::write(&path, b"not an mmdb").unwrap();
+        assert!(router.reload().is_err());
+        assert_eq!(selected(&router, &records, QueryClient::new(resolver)), "us-ca");
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_missing_database_uses_default() {
+        let router = GeoRouter::new(GeoDnsConfig {
+            database_path: "/nonexistent/geo.mmdb".into(),
+        });
+        assert_eq!(
+            selected(&router, &records(), QueryClient::new("10.1.4.4".parse().unwrap())),
+            "default"
+        );
+    }
+}
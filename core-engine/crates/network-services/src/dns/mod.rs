@@ -5,6 +5,14 @@ use chrono::{DateTime, Utc};
 
 use crate::error::NetworkResult;
 
+pub mod geo;
+pub mod querylog;
+pub mod resolver;
+
+pub use geo::{GeoDnsConfig, GeoRouter, QueryClient};
+pub use querylog::{QueryLogConfig, QueryLogSink, QueryLogger, ResponseCode};
+pub use resolver::{DnsAnswer, DnsQuery, DnsResolver};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Zone {
     pub id: String,
@@ -65,7 +73,7 @@ pub struct RecordSet {
     pub alias_target: Option<AliasTarget>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum RecordType {
     A,
     AAAA,
@@ -0,0 +1,468 @@
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, DurationRound, Utc};
+use serde::{Deserialize, Serialize};
+use sirsi_data_services::queue::{Message, MessageOperations};
+use sirsi_observability::monitoring::{MetricDataPoint, MetricValue, MetricsManager};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+use crate::error::{NetworkError, NetworkResult};
+use super::{RecordType, ZoneMetrics};
+
+pub const METRICS_NAMESPACE: &str = "SirsiNexus/DNS";
+pub const QUERIES_METRIC: &str = "dns_queries";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ResponseCode {
+    NoError,
+    ServFail,
+    NXDomain,
+    Refused,
+}
+
+impl ResponseCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ResponseCode::NoError => "NOERROR",
+            ResponseCode::ServFail => "SERVFAIL",
+            ResponseCode::NXDomain => "NXDOMAIN",
+            ResponseCode::Refused => "REFUSED",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueryLogRecord {
+    pub timestamp: DateTime<Utc>,
+    pub zone_id: Option<String>,
+    pub qname: String,
+    pub qtype: RecordType,
+    pub source: IpAddr,
+    pub response_code: ResponseCode,
+    pub latency_us: u64,
+    pub record_set_id: Option<String>,
+}
+
+#[async_trait]
+pub trait QueryLogSink: Send + Sync {
+    async fn write(&self, batch: &[QueryLogRecord]) -> NetworkResult<()>;
+}
+
+// One JSON object per line, appended
+pub struct FileSink {
+    path: PathBuf,
+}
+
+impl FileSink {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+#[async_trait]
+impl QueryLogSink for FileSink {
+    async fn write(&self, batch: &[QueryLogRecord]) -> NetworkResult<()> {
+        let mut out = Vec::new();
+        for record in batch {
+            serde_json::to_writer(&mut out, record)
+                .map_err(|e| NetworkError::Internal(format!("Failed to encode query log record: {}", e)))?;
+            out.push(b'\n');
+        }
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .map_err(|e| NetworkError::Internal(format!("Failed to open {}: {}", self.path.display(), e)))?;
+        file.write_all(&out)
+            .await
+            .map_err(|e| NetworkError::Internal(format!("Failed to write {}: {}", self.path.display(), e)))?;
+        Ok(())
+    }
+}
+
+// Folds each batch into one dns_queries counter per zone/qtype/response code
+pub struct MetricsSink {
+    manager: Arc<dyn MetricsManager>,
+}
+
+impl MetricsSink {
+    pub fn new(manager: Arc<dyn MetricsManager>) -> Self {
+        Self { manager }
+    }
+}
+
+#[async_trait]
+impl QueryLogSink for MetricsSink {
+    async fn write(&self, batch: &[QueryLogRecord]) -> NetworkResult<()> {
+        let mut counts: HashMap<(&str, String, &str), f64> = HashMap::new();
+        for record in batch {
+            let zone = record.zone_id.as_deref().unwrap_or("none");
+            *counts
+                .entry((zone, format!("{:?}", record.qtype), record.response_code.as_str()))
+                .or_default() += 1.0;
+        }
+        let timestamp = Utc::now();
+        let points = counts
+            .into_iter()
+            .map(|((zone, qtype, rcode), count)| MetricDataPoint {
+                name: QUERIES_METRIC.to_string(),
+                namespace: METRICS_NAMESPACE.to_string(),
+                dimensions: HashMap::from([
+                    ("zone_id".to_string(), zone.to_string()),
+                    ("qtype".to_string(), qtype),
+                    ("rcode".to_string(), rcode.to_string()),
+                ]),
+                timestamp,
+                value: MetricValue::Single(count),
+                exemplars: Vec::new(),
+            })
+            .collect();
+        self.manager.put_metric_data(points).await?;
+        Ok(())
+    }
+}
+
+// Publishes each record as a JSON message for downstream consumers
+pub struct QueueSink {
+    queue: Arc<dyn MessageOperations>,
+    queue_id: String,
+}
+
+impl QueueSink {
+    pub fn new(queue: Arc<dyn MessageOperations>, queue_id: impl Into<String>) -> Self {
+        Self {
+            queue,
+            queue_id: queue_id.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl QueryLogSink for QueueSink {
+    async fn write(&self, batch: &[QueryLogRecord]) -> NetworkResult<()> {
+        let mut messages = Vec::with_capacity(batch.len());
+        for record in batch {
+            let data = serde_json::to_vec(record)
+                .map_err(|e| NetworkError::Internal(format!("Failed to encode query log record: {}", e)))?;
+            messages.push(Message {
+                id: String::new(),
+                queue_id: self.queue_id.clone(),
+                data,
+                attributes: HashMap::from([("type".to_string(), "dns_query".to_string())]),
+                publish_time: record.timestamp,
+                delivery_count: 0,
+                scheduled_for: None,
+                correlation_id: None,
+                reply_to: None,
+            });
+        }
+        self.queue.send_batch(&self.queue_id, messages).await?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct QueryLogConfig {
+    pub channel_capacity: usize,
+    pub batch_size: usize,
+    pub flush_interval: Duration,
+    // Log one in every N queries; zones not listed use the default
+    pub default_sample_every: u64,
+    pub zone_sample_every: HashMap<String, u64>,
+    pub retention_minutes: usize,
+    // Distinct (qname, qtype, record set) keys kept per zone-minute; the rest only count towards totals
+    pub max_tracked_names: usize,
+    pub top_n: usize,
+}
+
+impl Default for QueryLogConfig {
+    fn default() -> Self {
+        Self {
+            channel_capacity: 10_000,
+            batch_size: 500,
+            flush_interval: Duration::from_secs(5),
+            default_sample_every: 1,
+            zone_sample_every: HashMap::new(),
+            retention_minutes: 24 * 60,
+            max_tracked_names: 10_000,
+            top_n: 20,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueryCount {
+    pub qname: String,
+    pub qtype: RecordType,
+    pub record_set_id: Option<String>,
+    pub count: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NxdomainReport {
+    pub zone_id: String,
+    pub total_queries: u64,
+    pub nxdomain_queries: u64,
+    pub nxdomain_ratio: f64,
+    pub top_names: Vec<QueryCount>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QueryLogStats {
+    pub logged: u64,
+    pub sampled_out: u64,
+    pub dropped: u64,
+    pub sink_errors: u64,
+}
+
+type QueryKey = (String, RecordType, Option<String>);
+
+#[derive(Default)]
+struct MinuteBucket {
+    total: u64,
+    latency_us_sum: u64,
+    nxdomain: u64,
+    queries: HashMap<QueryKey, u64>,
+    nxdomain_names: HashMap<QueryKey, u64>,
+}
+
+#[derive(Default)]
+struct ZoneAnalytics {
+    seen: u64,
+    buckets: VecDeque<(DateTime<Utc>, MinuteBucket)>,
+}
+
+#[derive(Default)]
+struct Stats {
+    logged: AtomicU64,
+    sampled_out: AtomicU64,
+    dropped: AtomicU64,
+    sink_errors: AtomicU64,
+}
+
+// Counts every query into per-zone minute buckets for analytics and forwards a sample to the
+// sink through a bounded channel. The resolver never waits on the sink: when the channel is
+// full the record is dropped and counted.
+pub struct QueryLogger {
+    config: QueryLogConfig,
+    sender: mpsc::Sender<QueryLogRecord>,
+    zones: Mutex<HashMap<String, ZoneAnalytics>>,
+    stats: Arc<Stats>,
+}
+
+impl QueryLogger {
+    pub fn new(config: QueryLogConfig, sink: Arc<dyn QueryLogSink>) -> (Arc<Self>, JoinHandle<()>) {
+        let (sender, receiver) = mpsc::channel(config.channel_capacity.max(1));
+        let stats = Arc::new(Stats::default());
+        let writer = tokio::spawn(write_loop(
+            receiver,
+            sink,
+            config.batch_size.max(1),
+            config.flush_interval,
+            stats.clone(),
+        ));
+        let logger = Arc::new(Self {
+            config,
+            sender,
+            zones: Mutex::new(HashMap::new()),
+            stats,
+        });
+        (logger, writer)
+    }
+
+    fn sample_every(&self, zone_id: Option<&str>) -> u64 {
+        zone_id
+            .and_then(|zone| self.config.zone_sample_every.get(zone))
+            .copied()
+            .unwrap_or(self.config.default_sample_every)
+            .max(1)
+    }
+
+    pub fn record(&self, record: QueryLogRecord) {
+        let sampled = match &record.zone_id {
+            Some(zone_id) => self.aggregate(zone_id, &record),
+            None => true,
+        };
+        if !sampled {
+            self.stats.sampled_out.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        match self.sender.try_send(record) {
+            Ok(()) => self.stats.logged.fetch_add(1, Ordering::Relaxed),
+            Err(_) => self.stats.dropped.fetch_add(1, Ordering::Relaxed),
+        };
+    }
+
+    // Returns whether this query is picked for the sink
+    fn aggregate(&self, zone_id: &str, record: &QueryLogRecord) -> bool {
+        let minute = record
+            .timestamp
+            .duration_trunc(chrono::Duration::minutes(1))
+            .unwrap_or(record.timestamp);
+        let key = (record.qname.clone(), record.qtype.clone(), record.record_set_id.clone());
+        let sample_every = self.sample_every(Some(zone_id));
+
+        let mut zones = self.zones.lock().expect("query analytics lock poisoned");
+        let zone = zones.entry(zone_id.to_string()).or_default();
+        zone.seen += 1;
+        if zone.buckets.back().is_none_or(|(at, _)| *at != minute) {
+            zone.buckets.push_back((minute, MinuteBucket::default()));
+            while zone.buckets.len() > self.config.retention_minutes {
+                zone.buckets.pop_front();
+            }
+        }
+        let (_, bucket) = zone.buckets.back_mut().expect("bucket was just pushed");
+        bucket.total += 1;
+        bucket.latency_us_sum += record.latency_us;
+        let nxdomain = record.response_code == ResponseCode::NXDomain;
+        if nxdomain {
+            bucket.nxdomain += 1;
+        }
+        let names = if nxdomain { &mut bucket.nxdomain_names } else { &mut bucket.queries };
+        if let Some(count) = names.get_mut(&key) {
+            *count += 1;
+        } else if names.len() < self.config.max_tracked_names {
+            names.insert(key, 1);
+        }
+        zone.seen.is_multiple_of(sample_every)
+    }
+
+    fn fold<T>(&self, zone_id: &str, window: chrono::Duration, f: impl FnMut(&MinuteBucket) -> T) -> Vec<T> {
+        let start = (Utc::now() - window)
+            .duration_trunc(chrono::Duration::minutes(1))
+            .unwrap_or_else(|_| Utc::now() - window);
+        let zones = self.zones.lock().expect("query analytics lock poisoned");
+        zones
+            .get(zone_id)
+            .map(|zone| {
+                zone.buckets
+                    .iter()
+                    .filter(|(at, _)| *at >= start)
+                    .map(|(_, bucket)| bucket)
+                    .map(f)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn ranked(&self, totals: HashMap<QueryKey, u64>) -> Vec<QueryCount> {
+        let mut ranked: Vec<QueryCount> = totals
+            .into_iter()
+            .map(|((qname, qtype, record_set_id), count)| QueryCount {
+                qname,
+                qtype,
+                record_set_id,
+                count,
+            })
+            .collect();
+        ranked.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.qname.cmp(&b.qname)));
+        ranked.truncate(self.config.top_n);
+        ranked
+    }
+
+    pub fn top_queries(&self, zone_id: &str, window: chrono::Duration) -> Vec<QueryCount> {
+        let mut totals = HashMap::new();
+        self.fold(zone_id, window, |bucket| {
+            for (key, count) in &bucket.queries {
+                *totals.entry(key.clone()).or_insert(0) += count;
+            }
+        });
+        self.ranked(totals)
+    }
+
+    pub fn nxdomain_report(&self, zone_id: &str, window: chrono::Duration) -> NxdomainReport {
+        let mut totals = HashMap::new();
+        let (mut total_queries, mut nxdomain_queries) = (0, 0);
+        self.fold(zone_id, window, |bucket| {
+            total_queries += bucket.total;
+            nxdomain_queries += bucket.nxdomain;
+            for (key, count) in &bucket.nxdomain_names {
+                *totals.entry(key.clone()).or_insert(0) += count;
+            }
+        });
+        NxdomainReport {
+            zone_id: zone_id.to_string(),
+            total_queries,
+            nxdomain_queries,
+            nxdomain_ratio: if total_queries == 0 {
+                0.0
+            } else {
+                nxdomain_queries as f64 / total_queries as f64
+            },
+            top_names: self.ranked(totals),
+        }
+    }
+
+    pub fn zone_metrics(&self, zone_id: &str, window: chrono::Duration) -> Vec<ZoneMetrics> {
+        let zones = self.zones.lock().expect("query analytics lock poisoned");
+        let start = Utc::now() - window;
+        zones
+            .get(zone_id)
+            .map(|zone| {
+                zone.buckets
+                    .iter()
+                    .filter(|(at, _)| *at >= start - chrono::Duration::minutes(1))
+                    .map(|(at, bucket)| ZoneMetrics {
+                        zone_id: zone_id.to_string(),
+                        timestamp: *at,
+                        queries_per_second: bucket.total as f64 / 60.0,
+                        total_queries: bucket.total as i64,
+                        latency_ms: bucket.latency_us_sum as f64 / bucket.total.max(1) as f64 / 1000.0,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn stats(&self) -> QueryLogStats {
+        QueryLogStats {
+            logged: self.stats.logged.load(Ordering::Relaxed),
+            sampled_out: self.stats.sampled_out.load(Ordering::Relaxed),
+            dropped: self.stats.dropped.load(Ordering::Relaxed),
+            sink_errors: self.stats.sink_errors.load(Ordering::Relaxed),
+        }
+    }
+}
+
+async fn write_loop(
+    mut receiver: mpsc::Receiver<QueryLogRecord>,
+    sink: Arc<dyn QueryLogSink>,
+    batch_size: usize,
+    flush_interval: Duration,
+    stats: Arc<Stats>,
+) {
+    let mut batch = Vec::with_capacity(batch_size);
+    let mut ticker = tokio::time::interval(flush_interval);
+    loop {
+        let (closed, flush) = tokio::select! {
+            received = receiver.recv() => match received {
+                Some(record) => {
+                    batch.push(record);
+                    (false, false)
+                }
+                None => (true, true),
+            },
+            _ = ticker.tick() => (false, true),
+        };
+        if !batch.is_empty() && (flush || batch.len() >= batch_size) {
+            if let Err(e) = sink.write(&batch).await {
+                warn!("Failed to write {} DNS query log records: {}", batch.len(), e);
+                stats.sink_errors.fetch_add(1, Ordering::Relaxed);
+            }
+            batch.clear();
+        }
+        if closed {
+            return;
+        }
+    }
+}
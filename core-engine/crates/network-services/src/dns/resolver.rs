@@ -0,0 +1,472 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
+
+use async_trait::async_trait;
+use chrono::Utc;
+
+use crate::error::{NetworkError, NetworkResult};
+use super::geo::{GeoRouter, QueryClient};
+use super::querylog::{QueryLogRecord, QueryLogger, ResponseCode};
+use super::{RecordSet, RecordSetManager, RecordType, RoutingPolicy, Zone, ZoneManager, ZoneMetrics};
+
+#[derive(Debug, Clone)]
+pub struct DnsQuery {
+    pub qname: String,
+    pub qtype: RecordType,
+    pub client: QueryClient,
+}
+
+#[derive(Debug, Clone)]
+pub struct DnsAnswer {
+    pub response_code: ResponseCode,
+    pub zone_id: Option<String>,
+    pub record_set_id: Option<String>,
+    pub ttl: i32,
+    pub records: Vec<String>,
+}
+
+impl DnsAnswer {
+    fn empty(response_code: ResponseCode, zone_id: Option<String>) -> Self {
+        Self {
+            response_code,
+            zone_id,
+            record_set_id: None,
+            ttl: 0,
+            records: Vec::new(),
+        }
+    }
+}
+
+fn normalize(name: &str) -> String {
+    name.trim_end_matches('.').to_ascii_lowercase()
+}
+
+struct ZoneEntry {
+    zone: Zone,
+    record_sets: Vec<RecordSet>,
+}
+
+// Authoritative resolver answering from zones held in memory
+pub struct DnsResolver {
+    zones: RwLock<HashMap<String, ZoneEntry>>,
+    geo: Option<Arc<GeoRouter>>,
+    query_log: Option<Arc<QueryLogger>>,
+}
+
+impl Default for DnsResolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DnsResolver {
+    pub fn new() -> Self {
+        Self {
+            zones: RwLock::new(HashMap::new()),
+            geo: None,
+            query_log: None,
+        }
+    }
+
+    pub fn with_geo(mut self, geo: Arc<GeoRouter>) -> Self {
+        self.geo = Some(geo);
+        self
+    }
+
+    pub fn with_query_log(mut self, query_log: Arc<QueryLogger>) -> Self {
+        self.query_log = Some(query_log);
+        self
+    }
+
+    pub fn query_log(&self) -> Option<&Arc<QueryLogger>> {
+        self.query_log.as_ref()
+    }
+
+    pub fn resolve(&self, query: &DnsQuery) -> DnsAnswer {
+        let started = Instant::now();
+        let answer = self.answer(query);
+        if let Some(query_log) = &self.query_log {
+            query_log.record(QueryLogRecord {
+                timestamp: Utc::now(),
+                zone_id: answer.zone_id.clone(),
+                qname: normalize(&query.qname),
+                qtype: query.qtype.clone(),
+                source: query.client.source_ip,
+                response_code: answer.response_code,
+                latency_us: started.elapsed().as_micros() as u64,
+                record_set_id: answer.record_set_id.clone(),
+            });
+        }
+        answer
+    }
+
+    fn answer(&self, query: &DnsQuery) -> DnsAnswer {
+        let qname = normalize(&query.qname);
+        let zones = self.zones.read().expect("zone lock poisoned");
+
+        // Longest matching zone apex wins
+        let Some(entry) = zones
+            .values()
+            .filter(|entry| {
+                let apex = normalize(&entry.zone.domain);
+                qname == apex || qname.ends_with(&format!(".{}", apex))
+            })
+            .max_by_key(|entry| entry.zone.domain.len())
+        else {
+            return DnsAnswer::empty(ResponseCode::Refused, None);
+        };
+        let zone_id = Some(entry.zone.id.clone());
+
+        let at_name: Vec<&RecordSet> = entry.record_sets.iter().filter(|r| normalize(&r.name) == qname).collect();
+        if at_name.is_empty() {
+            return DnsAnswer::empty(ResponseCode::NXDomain, zone_id);
+        }
+
+        // A CNAME at the name answers every other type
+        let wanted = if at_name.iter().any(|r| r.record_type == query.qtype) {
+            query.qtype.clone()
+        } else {
+            RecordType::CNAME
+        };
+        let candidates: Vec<RecordSet> = at_name.into_iter().filter(|r| r.record_type == wanted).cloned().collect();
+        if candidates.is_empty() {
+            return DnsAnswer::empty(ResponseCode::NoError, zone_id);
+        }
+
+        let geolocated = candidates
+            .iter()
+            .any(|r| matches!(r.routing_policy, Some(RoutingPolicy::Geolocation { .. })));
+        let selected = match (&self.geo, geolocated) {
+            (Some(geo), true) => geo.select(&candidates, &query.client),
+            _ => candidates.first(),
+        };
+        match selected {
+            Some(record) => DnsAnswer {
+                response_code: ResponseCode::NoError,
+                zone_id,
+                record_set_id: Some(record.id.clone()),
+                ttl: record.ttl,
+                records: record.records.clone(),
+            },
+            None => DnsAnswer::empty(ResponseCode::NoError, zone_id),
+        }
+    }
+}
+
+#[async_trait]
+impl ZoneManager for DnsResolver {
+    async fn create_zone(&self, zone: Zone) -> NetworkResult<Zone> {
+        let mut zones = self.zones.write().expect("zone lock poisoned");
+        if zones.contains_key(&zone.id) {
+            return Err(NetworkError::Conflict(format!("Zone {} already exists", zone.id)));
+        }
+        zones.insert(
+            zone.id.clone(),
+            ZoneEntry {
+                zone: zone.clone(),
+                record_sets: Vec::new(),
+            },
+        );
+        Ok(zone)
+    }
+
+    async fn modify_zone(&self, zone: Zone) -> NetworkResult<Zone> {
+        let mut zones = self.zones.write().expect("zone lock poisoned");
+        let entry = zones
+            .get_mut(&zone.id)
+            .ok_or_else(|| NetworkError::NotFound(format!("Zone {} not found", zone.id)))?;
+        entry.zone = zone.clone();
+        Ok(zone)
+    }
+
+    async fn delete_zone(&self, id: &str) -> NetworkResult<()> {
+        let mut zones = self.zones.write().expect("zone lock poisoned");
+        zones
+            .remove(id)
+            .map(|_| ())
+            .ok_or_else(|| NetworkError::NotFound(format!("Zone {} not found", id)))
+    }
+
+    async fn get_zone(&self, id: &str) -> NetworkResult<Zone> {
+        let zones = self.zones.read().expect("zone lock poisoned");
+        zones
+            .get(id)
+            .map(|entry| entry.zone.clone())
+            .ok_or_else(|| NetworkError::NotFound(format!("Zone {} not found", id)))
+    }
+
+    async fn list_zones(&self) -> NetworkResult<Vec<Zone>> {
+        let zones = self.zones.read().expect("zone lock poisoned");
+        Ok(zones.values().map(|entry| entry.zone.clone()).collect())
+    }
+
+    async fn get_metrics(&self, id: &str, window: chrono::Duration) -> NetworkResult<Vec<ZoneMetrics>> {
+        self.get_zone(id).await?;
+        Ok(self
+            .query_log
+            .as_ref()
+            .map(|query_log| query_log.zone_metrics(id, window))
+            .unwrap_or_default())
+    }
+}
+
+#[async_trait]
+impl RecordSetManager for DnsResolver {
+    async fn create_record_set(&self, record: RecordSet) -> NetworkResult<RecordSet> {
+        let mut zones = self.zones.write().expect("zone lock poisoned");
+        let entry = zones
+            .get_mut(&record.zone_id)
+            .ok_or_else(|| NetworkError::NotFound(format!("Zone {} not found", record.zone_id)))?;
+        if entry.record_sets.iter().any(|r| r.id == record.id) {
+            return Err(NetworkError::Conflict(format!("Record set {} already exists", record.id)));
+        }
+        entry.record_sets.push(record.clone());
+        Ok(record)
+    }
+
+    async fn modify_record_set(&self, record: RecordSet) -> NetworkResult<RecordSet> {
+        let mut zones = self.zones.write().expect("zone lock poisoned");
+        let existing = zones
+            .get_mut(&record.zone_id)
+            .and_then(|entry| entry.record_sets.iter_mut().find(|r| r.id == record.id))
+            .ok_or_else(|| NetworkError::NotFound(format!("Record set {} not found", record.id)))?;
+        *existing = record.clone();
+        Ok(record)
+    }
+
+    async fn delete_record_set(&self, zone_id: &str, record_id: &str) -> NetworkResult<()> {
+        let mut zones = self.zones.write().expect("zone lock poisoned");
+        let entry = zones
+            .get_mut(zone_id)
+            .ok_or_else(|| NetworkError::NotFound(format!("Zone {} not found", zone_id)))?;
+        let before = entry.record_sets.len();
+        entry.record_sets.retain(|r| r.id != record_id);
+        if entry.record_sets.len() == before {
+            return Err(NetworkError::NotFound(format!("Record set {} not found", record_id)));
+        }
+        Ok(())
+    }
+
+    async fn get_record_set(&self, zone_id: &str, record_id: &str) -> NetworkResult<RecordSet> {
+        let zones = self.zones.read().expect("zone lock poisoned");
+        zones
+            .get(zone_id)
+            .and_then(|entry| entry.record_sets.iter().find(|r| r.id == record_id))
+            .cloned()
+            .ok_or_else(|| NetworkError::NotFound(format!("Record set {} not found", record_id)))
+    }
+
+    async fn list_record_sets(&self, zone_id: &str) -> NetworkResult<Vec<RecordSet>> {
+        let zones = self.zones.read().expect("zone lock poisoned");
+        zones
+            .get(zone_id)
+            .map(|entry| entry.record_sets.clone())
+            .ok_or_else(|| NetworkError::NotFound(format!("Zone {} not found", zone_id)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns::querylog::{QueryLogConfig, QueryLogSink};
+    use crate::dns::{DnssecStatus, SOARecord, ZoneStatus, ZoneType};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[derive(Default)]
+    struct CollectingSink {
+        records: tokio::sync::Mutex<Vec<QueryLogRecord>>,
+    }
+
+    #[async_trait]
+    impl QueryLogSink for CollectingSink {
+        async fn write(&self, batch: &[QueryLogRecord]) -> NetworkResult<()> {
+            self.records.lock().await.extend_from_slice(batch);
+            Ok(())
+        }
+    }
+
+    // Accepts one batch, then never returns again
+    #[derive(Default)]
+    struct StuckSink {
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl QueryLogSink for StuckSink {
+        async fn write(&self, _batch: &[QueryLogRecord]) -> NetworkResult<()> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            futures::future::pending::<()>().await;
+            Ok(())
+        }
+    }
+
+    fn zone(id: &str, domain: &str) -> Zone {
+        Zone {
+            id: id.into(),
+            name: domain.into(),
+            domain: domain.into(),
+            zone_type: ZoneType::Public,
+            status: ZoneStatus::Active,
+            nameservers: vec!["ns1.example.com".into()],
+            soa_record: SOARecord {
+                mname: "ns1.example.com".into(),
+                rname: "hostmaster.example.com".into(),
+                serial: 1,
+                refresh: 7200,
+                retry: 900,
+                expire: 1_209_600,
+                minimum: 300,
+            },
+            dnssec_status: DnssecStatus::Disabled,
+            tags: HashMap::new(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    fn record(id: &str, name: &str, record_type: RecordType, value: &str) -> RecordSet {
+        RecordSet {
+            id: id.into(),
+            zone_id: "zone-1".into(),
+            name: name.into(),
+            record_type,
+            ttl: 300,
+            records: vec![value.into()],
+            routing_policy: Some(RoutingPolicy::Simple),
+            health_check: None,
+            alias_target: None,
+        }
+    }
+
+    async fn resolver(query_log: Arc<QueryLogger>) -> DnsResolver {
+        let resolver = DnsResolver::new().with_query_log(query_log);
+        resolver.create_zone(zone("zone-1", "example.com")).await.unwrap();
+        for record_set in [
+            record("www", "www.example.com", RecordType::A, "192.0.2.10"),
+            record("api", "api.example.com.", RecordType::A, "192.0.2.20"),
+            record("docs", "docs.example.com", RecordType::CNAME, "www.example.com"),
+        ] {
+            resolver.create_record_set(record_set).await.unwrap();
+        }
+        resolver
+    }
+
+    fn query(qname: &str, qtype: RecordType) -> DnsQuery {
+        DnsQuery {
+            qname: qname.into(),
+            qtype,
+            client: QueryClient::new("198.51.100.7".parse().unwrap()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_query_log_aggregation() {
+        let sink = Arc::new(CollectingSink::default());
+        let config = QueryLogConfig {
+            flush_interval: Duration::from_millis(10),
+            ..Default::default()
+        };
+        let (query_log, _writer) = QueryLogger::new(config, sink.clone());
+        let resolver = resolver(query_log.clone()).await;
+
+        for _ in 0..5 {
+            assert_eq!(resolver.resolve(&query("WWW.example.com.", RecordType::A)).records, vec!["192.0.2.10"]);
+        }
+        for _ in 0..2 {
+            resolver.resolve(&query("api.example.com", RecordType::A));
+        }
+        let cname = resolver.resolve(&query("docs.example.com", RecordType::A));
+        assert_eq!(cname.record_set_id.as_deref(), Some("docs"));
+        let nodata = resolver.resolve(&query("www.example.com", RecordType::AAAA));
+        assert_eq!((nodata.response_code, nodata.records.len()), (ResponseCode::NoError, 0));
+        for _ in 0..3 {
+            let answer = resolver.resolve(&query("old.example.com", RecordType::A));
+            assert_eq!(answer.response_code, ResponseCode::NXDomain);
+        }
+        resolver.resolve(&query("typo.example.com", RecordType::A));
+        let refused = resolver.resolve(&query("example.org", RecordType::A));
+        assert_eq!(refused.response_code, ResponseCode::Refused);
+
+        let top = query_log.top_queries("zone-1", chrono::Duration::hours(1));
+        let summary: Vec<(&str, Option<&str>, u64)> = top
+            .iter()
+            .map(|q| (q.qname.as_str(), q.record_set_id.as_deref(), q.count))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("www.example.com", Some("www"), 5),
+                ("api.example.com", Some("api"), 2),
+                ("docs.example.com", Some("docs"), 1),
+                ("www.example.com", None, 1),
+            ]
+        );
+
+        let report = query_log.nxdomain_report("zone-1", chrono::Duration::hours(1));
+        assert_eq!((report.total_queries, report.nxdomain_queries), (13, 4));
+        assert_eq!(report.top_names[0].qname, "old.example.com");
+        assert_eq!(report.top_names[0].count, 3);
+
+        let metrics = resolver.get_metrics("zone-1", chrono::Duration::hours(1)).await.unwrap();
+        assert_eq!(metrics.iter().map(|m| m.total_queries).sum::<i64>(), 13);
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let logged = sink.records.lock().await;
+        assert_eq!(logged.len(), 14);
+        assert!(logged.iter().any(|r| r.zone_id.is_none() && r.response_code == ResponseCode::Refused));
+    }
+
+    #[tokio::test]
+    async fn test_sampling_keeps_analytics_exact() {
+        let sink = Arc::new(CollectingSink::default());
+        let config = QueryLogConfig {
+            flush_interval: Duration::from_millis(10),
+            zone_sample_every: HashMap::from([("zone-1".to_string(), 10)]),
+            ..Default::default()
+        };
+        let (query_log, _writer) = QueryLogger::new(config, sink.clone());
+        let resolver = resolver(query_log.clone()).await;
+
+        for _ in 0..100 {
+            resolver.resolve(&query("www.example.com", RecordType::A));
+        }
+        assert_eq!(query_log.top_queries("zone-1", chrono::Duration::hours(1))[0].count, 100);
+        let stats = query_log.stats();
+        assert_eq!((stats.logged, stats.sampled_out), (10, 90));
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(sink.records.lock().await.len(), 10);
+    }
+
+    #[tokio::test]
+    async fn test_full_sink_never_blocks_resolution() {
+        let sink = Arc::new(StuckSink::default());
+        let config = QueryLogConfig {
+            channel_capacity: 8,
+            batch_size: 4,
+            flush_interval: Duration::from_millis(5),
+            ..Default::default()
+        };
+        let (query_log, _writer) = QueryLogger::new(config, sink.clone());
+        let resolver = resolver(query_log.clone()).await;
+
+        let resolved = tokio::time::timeout(Duration::from_secs(2), async {
+            for i in 0..10_000 {
+                resolver.resolve(&query("www.example.com", RecordType::A));
+                if i % 100 == 0 {
+                    tokio::task::yield_now().await;
+                }
+            }
+        })
+        .await;
+        assert!(resolved.is_ok(), "resolver blocked on the query log sink");
+
+        let stats = query_log.stats();
+        assert_eq!(sink.calls.load(Ordering::SeqCst), 1);
+        assert!(stats.dropped > 9_000);
+        assert_eq!(stats.logged + stats.dropped, 10_000);
+        assert_eq!(query_log.top_queries("zone-1", chrono::Duration::hours(1))[0].count, 10_000);
+    }
+}
@@ -0,0 +1,161 @@
+use sirsi_common::{ErrorKind, Retryable};
+use sirsi_data_services::DataError;
+use sirsi_observability::ObservabilityError;
+use thiserror::Error;
+use tonic::Status;
+
+#[derive(Error, Debug)]
+pub enum NetworkError {
+    #[error("Load balancer error: {0}")]
+    LoadBalancer(String),
+
+    #[error("DNS error: {0}")]
+    Dns(String),
+
+    #[error("VPN error: {0}")]
+    Vpn(String),
+
+    #[error("Network policy error: {0}")]
+    Policy(String),
+
+    #[error("Provider error: {0}")]
+    Provider(String),
+
+    #[error("Data service error: {0}")]
+    Data(#[from] DataError),
+
+    #[error("Observability error: {0}")]
+    Observability(#[from] ObservabilityError),
+
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+
+    #[error("Request throttled: {0}")]
+    Throttled(String),
+
+    #[error("Provider unavailable: {0}")]
+    Unavailable(String),
+
+    #[error("Authentication error: {0}")]
+    Auth(String),
+
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
+    #[error("Validation error: {0}")]
+    Validation(String),
+
+    #[error("Resource not found: {0}")]
+    NotFound(String),
+
+    #[error("Configuration error: {0}")]
+    Config(String),
+
+    #[error("Internal error: {0}")]
+    Internal(String),
+}
+
+impl NetworkError {
+    pub fn from_kind(kind: ErrorKind, msg: impl Into<String>) -> Self {
+        let msg = msg.into();
+        match kind {
+            ErrorKind::Throttled => NetworkError::Throttled(msg),
+            ErrorKind::ProviderOutage => NetworkError::Unavailable(msg),
+            ErrorKind::AuthFailure => NetworkError::Auth(msg),
+            ErrorKind::NotFound => NetworkError::NotFound(msg),
+            ErrorKind::Conflict => NetworkError::Conflict(msg),
+            ErrorKind::InvalidInput => NetworkError::Validation(msg),
+            ErrorKind::Internal => NetworkError::Provider(msg),
+        }
+    }
+
+    pub fn from_aws_code(code: Option<&str>, msg: impl Into<String>) -> Self {
+        let kind = code.map(ErrorKind::from_aws_code).unwrap_or(ErrorKind::Internal);
+        Self::from_kind(kind, msg)
+    }
+
+    pub fn from_gcp_status(http_code: Option<u16>, status: Option<&str>, msg: impl Into<String>) -> Self {
+        Self::from_kind(ErrorKind::from_gcp_status(http_code, status), msg)
+    }
+
+    pub fn from_http_status(status: u16, msg: impl Into<String>) -> Self {
+        Self::from_kind(ErrorKind::from_http_status(status), msg)
+    }
+}
+
+impl Retryable for NetworkError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            NetworkError::LoadBalancer(_) => ErrorKind::Internal,
+            NetworkError::Dns(_) => ErrorKind::Internal,
+            NetworkError::Vpn(_) => ErrorKind::Internal,
+            NetworkError::Policy(_) => ErrorKind::Internal,
+            NetworkError::Provider(_) => ErrorKind::Internal,
+            NetworkError::Data(e) => e.kind(),
+            NetworkError::Observability(e) => e.kind(),
+            NetworkError::Database(sqlx::Error::RowNotFound) => ErrorKind::NotFound,
+            NetworkError::Database(sqlx::Error::PoolTimedOut) | NetworkError::Database(sqlx::Error::Io(_)) => {
+                ErrorKind::ProviderOutage
+            }
+            NetworkError::Database(_) => ErrorKind::Internal,
+            NetworkError::Throttled(_) => ErrorKind::Throttled,
+            NetworkError::Unavailable(_) => ErrorKind::ProviderOutage,
+            NetworkError::Auth(_) => ErrorKind::AuthFailure,
+            NetworkError::Conflict(_) => ErrorKind::Conflict,
+            NetworkError::Validation(_) => ErrorKind::InvalidInput,
+            NetworkError::NotFound(_) => ErrorKind::NotFound,
+            NetworkError::Config(_) => ErrorKind::InvalidInput,
+            NetworkError::Internal(_) => ErrorKind::Internal,
+        }
+    }
+}
+
+impl From<NetworkError> for Status {
+    fn from(error: NetworkError) -> Self {
+        match error {
+            NetworkError::LoadBalancer(msg) => Status::internal(msg),
+            NetworkError::Dns(msg) => Status::internal(msg),
+            NetworkError::Vpn(msg) => Status::internal(msg),
+            NetworkError::Policy(msg) => Status::internal(msg),
+            NetworkError::Provider(msg) => Status::internal(msg),
+            NetworkError::Data(e) => e.into(),
+            NetworkError::Observability(e) => e.into(),
+            NetworkError::Database(e) => Status::internal(e.to_string()),
+            NetworkError::Throttled(msg) => Status::resource_exhausted(msg),
+            NetworkError::Unavailable(msg) => Status::unavailable(msg),
+            NetworkError::Auth(msg) => Status::unauthenticated(msg),
+            NetworkError::Conflict(msg) => Status::aborted(msg),
+            NetworkError::Validation(msg) => Status::invalid_argument(msg),
+            NetworkError::NotFound(msg) => Status::not_found(msg),
+            NetworkError::Config(msg) => Status::failed_precondition(msg),
+            NetworkError::Internal(msg) => Status::internal(msg),
+        }
+    }
+}
+
+pub type NetworkResult<T> = Result<T, NetworkError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_provider_errors_classify() {
+        let err = NetworkError::from_aws_code(Some("Throttling"), "Failed to change resource record sets");
+        assert!(matches!(err, NetworkError::Throttled(_)));
+        assert!(err.is_retryable());
+
+        let err = NetworkError::from_aws_code(Some("LoadBalancerNotFound"), "Failed to describe load balancer");
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+
+        let err = NetworkError::from_gcp_status(Some(503), None, "Failed to insert forwarding rule");
+        assert_eq!(err.kind(), ErrorKind::ProviderOutage);
+        assert!(err.is_retryable());
+    }
+
+    #[test]
+    fn test_domain_errors_are_permanent() {
+        assert!(!NetworkError::Dns("zone is not delegated".into()).is_retryable());
+        assert!(!NetworkError::Validation("invalid CIDR".into()).is_retryable());
+    }
+}
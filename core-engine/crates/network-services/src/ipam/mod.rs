@@ -0,0 +1,566 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::error::{NetworkError, NetworkResult};
+use crate::loadbalancer::rules::Cidr;
+use crate::peering::NetworkProvider;
+
+pub mod store;
+
+pub use store::{InMemoryIpamStore, PgIpamStore};
+
+// Each attempt that loses a race sees the winner's allocation on the next one
+const MAX_ALLOCATION_ATTEMPTS: usize = 16;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddressPool {
+    pub name: String,
+    pub cidr: String,
+    pub description: Option<String>,
+    pub tags: HashMap<String, String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl AddressPool {
+    pub fn new(name: impl Into<String>, cidr: impl Into<String>) -> Self {
+        Self { name: name.into(), cidr: cidr.into(), description: None, tags: HashMap::new(), created_at: Utc::now() }
+    }
+
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    pub fn with_tag(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.tags.insert(key.into(), value.into());
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AllocationKind {
+    // Handed out by `allocate_subnet`
+    Allocated,
+    // Held back from allocation, e.g. for a future region
+    Reserved,
+    // Recorded from a network that existed before IPAM tracked it
+    Imported,
+}
+
+impl AllocationKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AllocationKind::Allocated => "allocated",
+            AllocationKind::Reserved => "reserved",
+            AllocationKind::Imported => "imported",
+        }
+    }
+
+    pub fn parse(value: &str) -> NetworkResult<Self> {
+        match value {
+            "allocated" => Ok(AllocationKind::Allocated),
+            "reserved" => Ok(AllocationKind::Reserved),
+            "imported" => Ok(AllocationKind::Imported),
+            other => Err(NetworkError::Internal(format!("Unknown allocation kind {}", other))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SubnetAllocation {
+    pub id: Uuid,
+    pub pool: String,
+    pub cidr: String,
+    pub kind: AllocationKind,
+    pub tags: HashMap<String, String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl SubnetAllocation {
+    fn new(pool: &str, cidr: Block, kind: AllocationKind, tags: HashMap<String, String>) -> Self {
+        Self { id: Uuid::new_v4(), pool: pool.to_string(), cidr: cidr.to_string(), kind, tags, created_at: Utc::now() }
+    }
+}
+
+// A VPC, VNet or subnet that exists in a cloud account, whether or not IPAM knows about it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LiveNetwork {
+    pub provider: NetworkProvider,
+    pub account: String,
+    pub region: String,
+    pub resource_id: String,
+    pub cidr: String,
+}
+
+// Source of live networks. The agent connectors' `vpc` and `subnet` discovery reports each
+// network's address range as the `cidr_block` metadata entry, which maps onto `LiveNetwork`.
+#[async_trait]
+pub trait NetworkInventory: Send + Sync {
+    async fn live_networks(&self) -> NetworkResult<Vec<LiveNetwork>>;
+}
+
+// Recording an allocation is the only guard against two requests picking the same block, so
+// `insert_allocation` must reject a block overlapping any other in its pool with
+// `NetworkError::Conflict`, atomically with the insert.
+#[async_trait]
+pub trait IpamStore: Send + Sync {
+    async fn create_pool(&self, pool: &AddressPool) -> NetworkResult<()>;
+    async fn get_pool(&self, name: &str) -> NetworkResult<Option<AddressPool>>;
+    async fn list_pools(&self) -> NetworkResult<Vec<AddressPool>>;
+    async fn insert_allocation(&self, allocation: &SubnetAllocation) -> NetworkResult<()>;
+    async fn delete_allocation(&self, pool: &str, cidr: &str) -> NetworkResult<Option<SubnetAllocation>>;
+    async fn list_allocations(&self, pool: &str) -> NetworkResult<Vec<SubnetAllocation>>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum IpamConflict {
+    Allocation(SubnetAllocation),
+    Live(LiveNetwork),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PoolUtilization {
+    pub pool: String,
+    pub cidr: String,
+    pub total_addresses: u128,
+    // Allocated and imported blocks
+    pub allocated_addresses: u128,
+    pub reserved_addresses: u128,
+    pub free_addresses: u128,
+    pub allocations: usize,
+    // Share of the pool allocated or reserved, 0-100
+    pub utilization_percent: f64,
+    // Shortest prefix that could still be allocated, if any
+    pub largest_free_prefix: Option<u8>,
+}
+
+// A CIDR block as a range of addresses; IPv4 addresses occupy the low 32 bits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Block {
+    pub(crate) v6: bool,
+    pub(crate) start: u128,
+    pub(crate) prefix: u8,
+}
+
+impl Block {
+    pub(crate) fn host(address: IpAddr) -> Self {
+        match address {
+            IpAddr::V4(address) => Self { v6: false, start: u32::from(address) as u128, prefix: 32 },
+            IpAddr::V6(address) => Self { v6: true, start: u128::from(address), prefix: 128 },
+        }
+    }
+
+    pub(crate) fn parse(value: &str) -> NetworkResult<Self> {
+        let cidr = Cidr::parse(value)?;
+        let (v6, start) = match cidr.network() {
+            IpAddr::V4(address) => (false, u32::from(address) as u128),
+            IpAddr::V6(address) => (true, u128::from(address)),
+        };
+        let block = Self { v6, start, prefix: cidr.prefix() };
+        if start & block.host_mask() != 0 {
+            let network = Self { start: start & !block.host_mask(), ..block };
+            return Err(NetworkError::Validation(format!("{} has host bits set; did you mean {}?", value, network)));
+        }
+        Ok(block)
+    }
+
+    pub(crate) fn width(&self) -> u8 {
+        if self.v6 {
+            128
+        } else {
+            32
+        }
+    }
+
+    pub(crate) fn mask_for(width: u8, prefix: u8) -> u128 {
+        if prefix >= width {
+            0
+        } else {
+            u128::MAX >> (128 - (width - prefix) as u32)
+        }
+    }
+
+    pub(crate) fn host_mask(&self) -> u128 {
+        Self::mask_for(self.width(), self.prefix)
+    }
+
+    pub(crate) fn end(&self) -> u128 {
+        self.start | self.host_mask()
+    }
+
+    pub(crate) fn size(&self) -> u128 {
+        self.host_mask().saturating_add(1)
+    }
+
+    pub(crate) fn overlaps(&self, other: &Block) -> bool {
+        self.v6 == other.v6 && self.start <= other.end() && other.start <= self.end()
+    }
+
+    pub(crate) fn contains(&self, other: &Block) -> bool {
+        self.v6 == other.v6 && self.start <= other.start && other.end() <= self.end()
+    }
+}
+
+impl fmt::Display for Block {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.v6 {
+            write!(f, "{}/{}", Ipv6Addr::from(self.start), self.prefix)
+        } else {
+            write!(f, "{}/{}", Ipv4Addr::from(self.start as u32), self.prefix)
+        }
+    }
+}
+
+// Lowest block of `prefix` inside `pool` clear of everything in `taken`
+fn first_fit(pool: &Block, prefix: u8, taken: &mut [Block]) -> Option<Block> {
+    let mask = Block::mask_for(pool.width(), prefix);
+    taken.sort_by_key(|block| block.start);
+    let mut candidate = pool.start;
+    for block in taken.iter().filter(|block| block.overlaps(pool)) {
+        if block.end() < candidate {
+            continue;
+        }
+        if block.start > candidate | mask {
+            break;
+        }
+        candidate = block.end().checked_add(1)?.checked_add(mask)? & !mask;
+    }
+    let block = Block { v6: pool.v6, start: candidate, prefix };
+    pool.contains(&block).then_some(block)
+}
+
+pub struct IpamManager {
+    store: Arc<dyn IpamStore>,
+    inventory: Option<Arc<dyn NetworkInventory>>,
+}
+
+impl IpamManager {
+    pub fn new(store: Arc<dyn IpamStore>) -> Self {
+        Self { store, inventory: None }
+    }
+
+    pub fn with_inventory(mut self, inventory: Arc<dyn NetworkInventory>) -> Self {
+        self.inventory = Some(inventory);
+        self
+    }
+
+    pub async fn register_pool(&self, mut pool: AddressPool) -> NetworkResult<AddressPool> {
+        let block = Block::parse(&pool.cidr)?;
+        pool.cidr = block.to_string();
+        for existing in self.store.list_pools().await? {
+            if Block::parse(&existing.cidr)?.overlaps(&block) {
+                return Err(NetworkError::Conflict(format!(
+                    "Pool {} ({}) overlaps pool {} ({})",
+                    pool.name, pool.cidr, existing.name, existing.cidr
+                )));
+            }
+        }
+        self.store.create_pool(&pool).await?;
+        info!("Registered address pool {} ({})", pool.name, pool.cidr);
+        Ok(pool)
+    }
+
+    pub async fn list_pools(&self) -> NetworkResult<Vec<AddressPool>> {
+        self.store.list_pools().await
+    }
+
+    pub async fn list_allocations(&self, pool: &str) -> NetworkResult<Vec<SubnetAllocation>> {
+        self.store.list_allocations(pool).await
+    }
+
+    // Next free block of `prefix_len` in the pool, skipping recorded allocations and
+    // reservations as well as live networks IPAM has no record of
+    pub async fn allocate_subnet(
+        &self,
+        pool: &str,
+        prefix_len: u8,
+        tags: HashMap<String, String>,
+    ) -> NetworkResult<SubnetAllocation> {
+        let range = self.pool_block(pool).await?;
+        if prefix_len < range.prefix || prefix_len > range.width() {
+            return Err(NetworkError::Validation(format!(
+                "Cannot allocate a /{} from pool {} ({})",
+                prefix_len, pool, range
+            )));
+        }
+        let live: Vec<Block> = self.live_networks(&range).await?.into_iter().map(|(block, _)| block).collect();
+
+        for _ in 0..MAX_ALLOCATION_ATTEMPTS {
+            let mut taken = self.recorded_blocks(pool).await?;
+            taken.extend(live.iter().copied());
+            let Some(subnet) = first_fit(&range, prefix_len, &mut taken) else {
+                return Err(NetworkError::Conflict(format!("Pool {} has no free /{} left", pool, prefix_len)));
+            };
+            let allocation = SubnetAllocation::new(pool, subnet, AllocationKind::Allocated, tags.clone());
+            match self.store.insert_allocation(&allocation).await {
+                Ok(()) => {
+                    info!("Allocated {} from pool {}", allocation.cidr, pool);
+                    return Ok(allocation);
+                }
+                // A concurrent request took an overlapping block after we listed the pool
+                Err(NetworkError::Conflict(_)) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Err(NetworkError::Conflict(format!(
+            "Gave up allocating a /{} from pool {} after {} conflicting attempts",
+            prefix_len, pool, MAX_ALLOCATION_ATTEMPTS
+        )))
+    }
+
+    pub async fn release_subnet(&self, pool: &str, cidr: &str) -> NetworkResult<SubnetAllocation> {
+        let cidr = Block::parse(cidr)?.to_string();
+        let released = self
+            .store
+            .delete_allocation(pool, &cidr)
+            .await?
+            .ok_or_else(|| NetworkError::NotFound(format!("No allocation {} in pool {}", cidr, pool)))?;
+        info!("Released {} from pool {}", cidr, pool);
+        Ok(released)
+    }
+
+    pub async fn reserve_range(&self, pool: &str, cidr: &str, tags: HashMap<String, String>) -> NetworkResult<SubnetAllocation> {
+        self.record(pool, cidr, AllocationKind::Reserved, tags).await
+    }
+
+    // Only recorded allocations conflict with an import: the live network being imported is
+    // usually the one it describes
+    pub async fn import_allocation(&self, pool: &str, cidr: &str, tags: HashMap<String, String>) -> NetworkResult<SubnetAllocation> {
+        self.record(pool, cidr, AllocationKind::Imported, tags).await
+    }
+
+    // Everything overlapping `cidr`, for checking a block chosen outside IPAM
+    pub async fn conflicts(&self, cidr: &str) -> NetworkResult<Vec<IpamConflict>> {
+        let block = Block::parse(cidr)?;
+        let mut conflicts = Vec::new();
+        for pool in self.store.list_pools().await? {
+            if !Block::parse(&pool.cidr)?.overlaps(&block) {
+                continue;
+            }
+            for allocation in self.store.list_allocations(&pool.name).await? {
+                if Block::parse(&allocation.cidr)?.overlaps(&block) {
+                    conflicts.push(IpamConflict::Allocation(allocation));
+                }
+            }
+        }
+        conflicts.extend(self.live_networks(&block).await?.into_iter().map(|(_, network)| IpamConflict::Live(network)));
+        Ok(conflicts)
+    }
+
+    pub async fn utilization(&self, pool: &str) -> NetworkResult<PoolUtilization> {
+        let range = self.pool_block(pool).await?;
+        let allocations = self.store.list_allocations(pool).await?;
+        let (mut allocated, mut reserved) = (0u128, 0u128);
+        let mut taken = Vec::with_capacity(allocations.len());
+        for allocation in &allocations {
+            let block = Block::parse(&allocation.cidr)?;
+            match allocation.kind {
+                AllocationKind::Reserved => reserved = reserved.saturating_add(block.size()),
+                AllocationKind::Allocated | AllocationKind::Imported => allocated = allocated.saturating_add(block.size()),
+            }
+            taken.push(block);
+        }
+        let total = range.size();
+        let used = allocated.saturating_add(reserved);
+        let largest_free_prefix = (range.prefix..=range.width()).find(|prefix| first_fit(&range, *prefix, &mut taken).is_some());
+        Ok(PoolUtilization {
+            pool: pool.to_string(),
+            cidr: range.to_string(),
+            total_addresses: total,
+            allocated_addresses: allocated,
+            reserved_addresses: reserved,
+            free_addresses: total.saturating_sub(used),
+            allocations: allocations.len(),
+            utilization_percent: used as f64 / total as f64 * 100.0,
+            largest_free_prefix,
+        })
+    }
+
+    async fn record(
+        &self,
+        pool: &str,
+        cidr: &str,
+        kind: AllocationKind,
+        tags: HashMap<String, String>,
+    ) -> NetworkResult<SubnetAllocation> {
+        let range = self.pool_block(pool).await?;
+        let block = Block::parse(cidr)?;
+        if !range.contains(&block) {
+            return Err(NetworkError::Validation(format!("{} is outside pool {} ({})", block, pool, range)));
+        }
+        let allocation = SubnetAllocation::new(pool, block, kind, tags);
+        self.store.insert_allocation(&allocation).await?;
+        info!("Recorded {} {} in pool {}", kind.as_str(), allocation.cidr, pool);
+        Ok(allocation)
+    }
+
+    async fn pool_block(&self, pool: &str) -> NetworkResult<Block> {
+        let pool = self
+            .store
+            .get_pool(pool)
+            .await?
+            .ok_or_else(|| NetworkError::NotFound(format!("Address pool {} not found", pool)))?;
+        Block::parse(&pool.cidr)
+    }
+
+    async fn recorded_blocks(&self, pool: &str) -> NetworkResult<Vec<Block>> {
+        self.store.list_allocations(pool).await?.iter().map(|allocation| Block::parse(&allocation.cidr)).collect()
+    }
+
+    // Live networks overlapping `range`. A failed lookup fails the caller: allocating without
+    // it could hand out a block that is already in use.
+    async fn live_networks(&self, range: &Block) -> NetworkResult<Vec<(Block, LiveNetwork)>> {
+        let Some(inventory) = &self.inventory else {
+            return Ok(Vec::new());
+        };
+        let mut overlapping = Vec::new();
+        for network in inventory.live_networks().await? {
+            match Block::parse(&network.cidr) {
+                Ok(block) if block.overlaps(range) => overlapping.push((block, network)),
+                Ok(_) => {}
+                Err(e) => warn!("Ignoring live network {} with unusable CIDR: {}", network.resource_id, e),
+            }
+        }
+        Ok(overlapping)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    // Yields while listing so concurrent allocations all plan against the same snapshot
+    struct Interleaved(InMemoryIpamStore);
+
+    #[async_trait]
+    impl IpamStore for Interleaved {
+        async fn create_pool(&self, pool: &AddressPool) -> NetworkResult<()> {
+            self.0.create_pool(pool).await
+        }
+
+        async fn get_pool(&self, name: &str) -> NetworkResult<Option<AddressPool>> {
+            self.0.get_pool(name).await
+        }
+
+        async fn list_pools(&self) -> NetworkResult<Vec<AddressPool>> {
+            self.0.list_pools().await
+        }
+
+        async fn insert_allocation(&self, allocation: &SubnetAllocation) -> NetworkResult<()> {
+            self.0.insert_allocation(allocation).await
+        }
+
+        async fn delete_allocation(&self, pool: &str, cidr: &str) -> NetworkResult<Option<SubnetAllocation>> {
+            self.0.delete_allocation(pool, cidr).await
+        }
+
+        async fn list_allocations(&self, pool: &str) -> NetworkResult<Vec<SubnetAllocation>> {
+            let allocations = self.0.list_allocations(pool).await;
+            tokio::task::yield_now().await;
+            allocations
+        }
+    }
+
+    struct StaticInventory(Vec<LiveNetwork>);
+
+    #[async_trait]
+    impl NetworkInventory for StaticInventory {
+        async fn live_networks(&self) -> NetworkResult<Vec<LiveNetwork>> {
+            Ok(self.0.clone())
+        }
+    }
+
+    fn live(resource_id: &str, cidr: &str) -> LiveNetwork {
+        LiveNetwork {
+            provider: NetworkProvider::Aws,
+            account: "111122223333".into(),
+            region: "us-east-1".into(),
+            resource_id: resource_id.into(),
+            cidr: cidr.into(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_allocations_never_overlap() {
+        let ipam = Arc::new(IpamManager::new(Arc::new(Interleaved(InMemoryIpamStore::new()))));
+        ipam.register_pool(AddressPool::new("prod", "10.0.0.0/16")).await.unwrap();
+
+        let requests: Vec<_> = (0..12)
+            .map(|_| {
+                let ipam = ipam.clone();
+                tokio::spawn(async move { ipam.allocate_subnet("prod", 24, HashMap::new()).await })
+            })
+            .collect();
+        let mut blocks = Vec::new();
+        for request in requests {
+            blocks.push(Block::parse(&request.await.unwrap().unwrap().cidr).unwrap());
+        }
+        for (i, a) in blocks.iter().enumerate() {
+            assert!(blocks[i + 1..].iter().all(|b| !a.overlaps(b)), "{} overlaps another allocation", a);
+        }
+        let expected: HashSet<String> = (0..12).map(|i| format!("10.0.{}.0/24", i)).collect();
+        assert_eq!(blocks.iter().map(Block::to_string).collect::<HashSet<_>>(), expected);
+    }
+
+    #[tokio::test]
+    async fn test_prefix_length_edges() {
+        let ipam = IpamManager::new(Arc::new(InMemoryIpamStore::new()));
+        ipam.register_pool(AddressPool::new("edge", "192.168.10.0/24")).await.unwrap();
+        assert!(matches!(ipam.allocate_subnet("edge", 23, HashMap::new()).await, Err(NetworkError::Validation(_))));
+        assert!(matches!(ipam.allocate_subnet("edge", 33, HashMap::new()).await, Err(NetworkError::Validation(_))));
+
+        let whole = ipam.allocate_subnet("edge", 24, HashMap::new()).await.unwrap();
+        assert_eq!(whole.cidr, "192.168.10.0/24");
+        assert!(matches!(ipam.allocate_subnet("edge", 32, HashMap::new()).await, Err(NetworkError::Conflict(_))));
+        let full = ipam.utilization("edge").await.unwrap();
+        assert_eq!((full.total_addresses, full.free_addresses, full.largest_free_prefix), (256, 0, None));
+
+        ipam.release_subnet("edge", "192.168.10.0/24").await.unwrap();
+        ipam.reserve_range("edge", "192.168.10.0/32", HashMap::new()).await.unwrap();
+        let host = ipam.allocate_subnet("edge", 32, HashMap::new()).await.unwrap();
+        assert_eq!(host.cidr, "192.168.10.1/32");
+        // The next /25 has to skip the two single hosts at the bottom of the range
+        assert_eq!(ipam.allocate_subnet("edge", 25, HashMap::new()).await.unwrap().cidr, "192.168.10.128/25");
+
+        let usage = ipam.utilization("edge").await.unwrap();
+        assert_eq!((usage.allocated_addresses, usage.reserved_addresses, usage.free_addresses), (129, 1, 126));
+        assert_eq!(usage.largest_free_prefix, Some(26));
+        assert!(matches!(
+            ipam.import_allocation("edge", "192.168.10.0/30", HashMap::new()).await,
+            Err(NetworkError::Conflict(_))
+        ));
+        assert!(matches!(ipam.register_pool(AddressPool::new("bad", "10.0.0.1/8")).await, Err(NetworkError::Validation(_))));
+    }
+
+    #[tokio::test]
+    async fn test_allocation_skips_live_networks() {
+        let inventory = StaticInventory(vec![
+            live("vpc-legacy", "10.20.0.0/24"),
+            live("subnet-app", "10.20.1.0/25"),
+            live("vpc-elsewhere", "172.16.0.0/16"),
+            live("vpc-v6", "2600:1f18::/56"),
+        ]);
+        let ipam = IpamManager::new(Arc::new(InMemoryIpamStore::new())).with_inventory(Arc::new(inventory));
+        ipam.register_pool(AddressPool::new("prod", "10.20.0.0/16")).await.unwrap();
+
+        assert_eq!(ipam.allocate_subnet("prod", 24, HashMap::new()).await.unwrap().cidr, "10.20.2.0/24");
+        let conflicts = ipam.conflicts("10.20.1.0/24").await.unwrap();
+        assert_eq!(conflicts, vec![IpamConflict::Live(live("subnet-app", "10.20.1.0/25"))]);
+
+        // Importing the legacy VPC records it without tripping over the live network itself
+        ipam.import_allocation("prod", "10.20.0.0/24", HashMap::new()).await.unwrap();
+        let conflicts = ipam.conflicts("10.20.0.0/23").await.unwrap();
+        assert_eq!(conflicts.len(), 3);
+        assert!(matches!(&conflicts[0], IpamConflict::Allocation(a) if a.kind == AllocationKind::Imported));
+    }
+}
@@ -0,0 +1,256 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use chrono::{DateTime, TimeZone, Utc};
+use sqlx::postgres::PgRow;
+use sqlx::types::Json;
+use sqlx::{PgPool, Row};
+
+use super::{AddressPool, AllocationKind, Block, IpamStore, SubnetAllocation};
+use crate::error::{NetworkError, NetworkResult};
+
+// Exclusion and unique constraint violations
+const OVERLAP_CODES: [&str; 2] = ["23P01", "23505"];
+
+#[derive(Default)]
+struct IpamState {
+    pools: HashMap<String, AddressPool>,
+    allocations: HashMap<String, Vec<SubnetAllocation>>,
+}
+
+#[derive(Default)]
+pub struct InMemoryIpamStore {
+    state: Mutex<IpamState>,
+}
+
+impl InMemoryIpamStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl IpamStore for InMemoryIpamStore {
+    async fn create_pool(&self, pool: &AddressPool) -> NetworkResult<()> {
+        let mut state = self.state.lock().unwrap();
+        if state.pools.contains_key(&pool.name) {
+            return Err(NetworkError::Conflict(format!("Address pool {} already exists", pool.name)));
+        }
+        state.pools.insert(pool.name.clone(), pool.clone());
+        Ok(())
+    }
+
+    async fn get_pool(&self, name: &str) -> NetworkResult<Option<AddressPool>> {
+        Ok(self.state.lock().unwrap().pools.get(name).cloned())
+    }
+
+    async fn list_pools(&self) -> NetworkResult<Vec<AddressPool>> {
+        let mut pools: Vec<_> = self.state.lock().unwrap().pools.values().cloned().collect();
+        pools.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(pools)
+    }
+
+    async fn insert_allocation(&self, allocation: &SubnetAllocation) -> NetworkResult<()> {
+        let block = Block::parse(&allocation.cidr)?;
+        let mut state = self.state.lock().unwrap();
+        if !state.pools.contains_key(&allocation.pool) {
+            return Err(NetworkError::NotFound(format!("Address pool {} not found", allocation.pool)));
+        }
+        let allocations = state.allocations.entry(allocation.pool.clone()).or_default();
+        for existing in allocations.iter() {
+            if Block::parse(&existing.cidr)?.overlaps(&block) {
+                return Err(NetworkError::Conflict(format!(
+                    "{} overlaps {} in pool {}",
+                    allocation.cidr, existing.cidr, allocation.pool
+                )));
+            }
+        }
+        allocations.push(allocation.clone());
+        Ok(())
+    }
+
+    async fn delete_allocation(&self, pool: &str, cidr: &str) -> NetworkResult<Option<SubnetAllocation>> {
+        let mut state = self.state.lock().unwrap();
+        let Some(allocations) = state.allocations.get_mut(pool) else {
+            return Ok(None);
+        };
+        Ok(allocations.iter().position(|a| a.cidr == cidr).map(|i| allocations.remove(i)))
+    }
+
+    async fn list_allocations(&self, pool: &str) -> NetworkResult<Vec<SubnetAllocation>> {
+        Ok(self.state.lock().unwrap().allocations.get(pool).cloned().unwrap_or_default())
+    }
+}
+
+// The exclusion constraint on `ipam_allocations` is what keeps concurrent allocations from
+// overlapping: whichever insert commits second fails and the allocator plans again.
+pub struct PgIpamStore {
+    db: PgPool,
+}
+
+impl PgIpamStore {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+
+    pub async fn ensure_schema(&self) -> NetworkResult<()> {
+        sqlx::query("CREATE EXTENSION IF NOT EXISTS btree_gist").execute(&self.db).await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS ipam_pools (
+                name TEXT PRIMARY KEY,
+                cidr CIDR NOT NULL,
+                description TEXT,
+                tags JSONB NOT NULL,
+                created_at_ms BIGINT NOT NULL
+            )",
+        )
+        .execute(&self.db)
+        .await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS ipam_allocations (
+                id UUID PRIMARY KEY,
+                pool_name TEXT NOT NULL REFERENCES ipam_pools (name),
+                cidr CIDR NOT NULL,
+                kind TEXT NOT NULL,
+                tags JSONB NOT NULL,
+                created_at_ms BIGINT NOT NULL,
+                EXCLUDE USING gist (pool_name WITH =, cidr inet_ops WITH &&)
+            )",
+        )
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+
+    fn pool_from_row(row: &PgRow) -> NetworkResult<AddressPool> {
+        Ok(AddressPool {
+            name: row.try_get("name")?,
+            cidr: row.try_get("cidr")?,
+            description: row.try_get("description")?,
+            tags: row.try_get::<Json<HashMap<String, String>>, _>("tags")?.0,
+            created_at: from_millis(row.try_get("created_at_ms")?)?,
+        })
+    }
+
+    fn allocation_from_row(row: &PgRow) -> NetworkResult<SubnetAllocation> {
+        Ok(SubnetAllocation {
+            id: row.try_get("id")?,
+            pool: row.try_get("pool_name")?,
+            cidr: row.try_get("cidr")?,
+            kind: AllocationKind::parse(row.try_get("kind")?)?,
+            tags: row.try_get::<Json<HashMap<String, String>>, _>("tags")?.0,
+            created_at: from_millis(row.try_get("created_at_ms")?)?,
+        })
+    }
+}
+
+fn from_millis(ms: i64) -> NetworkResult<DateTime<Utc>> {
+    Utc.timestamp_millis_opt(ms)
+        .single()
+        .ok_or_else(|| NetworkError::Internal(format!("Invalid timestamp {}", ms)))
+}
+
+fn overlap_as_conflict(error: sqlx::Error, message: impl FnOnce() -> String) -> NetworkError {
+    match &error {
+        sqlx::Error::Database(db) if db.code().is_some_and(|code| OVERLAP_CODES.contains(&code.as_ref())) => {
+            NetworkError::Conflict(message())
+        }
+        _ => error.into(),
+    }
+}
+
+#[async_trait]
+impl IpamStore for PgIpamStore {
+    async fn create_pool(&self, pool: &AddressPool) -> NetworkResult<()> {
+        sqlx::query(
+            "INSERT INTO ipam_pools (name, cidr, description, tags, created_at_ms)
+             VALUES ($1, $2::cidr, $3, $4, $5)",
+        )
+        .bind(&pool.name)
+        .bind(&pool.cidr)
+        .bind(&pool.description)
+        .bind(Json(&pool.tags))
+        .bind(pool.created_at.timestamp_millis())
+        .execute(&self.db)
+        .await
+        .map_err(|e| overlap_as_conflict(e, || format!("Address pool {} already exists", pool.name)))?;
+        Ok(())
+    }
+
+    async fn get_pool(&self, name: &str) -> NetworkResult<Option<AddressPool>> {
+        let row = sqlx::query(
+            "SELECT name, cidr::text AS cidr, description, tags, created_at_ms FROM ipam_pools WHERE name = $1",
+        )
+        .bind(name)
+        .fetch_optional(&self.db)
+        .await?;
+        row.as_ref().map(Self::pool_from_row).transpose()
+    }
+
+    async fn list_pools(&self) -> NetworkResult<Vec<AddressPool>> {
+        let rows = sqlx::query("SELECT name, cidr::text AS cidr, description, tags, created_at_ms FROM ipam_pools ORDER BY name")
+            .fetch_all(&self.db)
+            .await?;
+        rows.iter().map(Self::pool_from_row).collect()
+    }
+
+    async fn insert_allocation(&self, allocation: &SubnetAllocation) -> NetworkResult<()> {
+        let mut tx = self.db.begin().await?;
+        // Holds the pool row until commit and checks the block lies inside it
+        let inside: Option<bool> = sqlx::query_scalar("SELECT cidr >>= $2::cidr FROM ipam_pools WHERE name = $1 FOR SHARE")
+            .bind(&allocation.pool)
+            .bind(&allocation.cidr)
+            .fetch_optional(&mut *tx)
+            .await?;
+        match inside {
+            None => return Err(NetworkError::NotFound(format!("Address pool {} not found", allocation.pool))),
+            Some(false) => {
+                return Err(NetworkError::Validation(format!("{} is outside pool {}", allocation.cidr, allocation.pool)))
+            }
+            Some(true) => {}
+        }
+        sqlx::query(
+            "INSERT INTO ipam_allocations (id, pool_name, cidr, kind, tags, created_at_ms)
+             VALUES ($1, $2, $3::cidr, $4, $5, $6)",
+        )
+        .bind(allocation.id)
+        .bind(&allocation.pool)
+        .bind(&allocation.cidr)
+        .bind(allocation.kind.as_str())
+        .bind(Json(&allocation.tags))
+        .bind(allocation.created_at.timestamp_millis())
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            overlap_as_conflict(e, || {
+                format!("{} overlaps an existing allocation in pool {}", allocation.cidr, allocation.pool)
+            })
+        })?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn delete_allocation(&self, pool: &str, cidr: &str) -> NetworkResult<Option<SubnetAllocation>> {
+        let row = sqlx::query(
+            "DELETE FROM ipam_allocations WHERE pool_name = $1 AND cidr = $2::cidr
+             RETURNING id, pool_name, cidr::text AS cidr, kind, tags, created_at_ms",
+        )
+        .bind(pool)
+        .bind(cidr)
+        .fetch_optional(&self.db)
+        .await?;
+        row.as_ref().map(Self::allocation_from_row).transpose()
+    }
+
+    async fn list_allocations(&self, pool: &str) -> NetworkResult<Vec<SubnetAllocation>> {
+        let rows = sqlx::query(
+            "SELECT id, pool_name, cidr::text AS cidr, kind, tags, created_at_ms
+             FROM ipam_allocations WHERE pool_name = $1 ORDER BY cidr",
+        )
+        .bind(pool)
+        .fetch_all(&self.db)
+        .await?;
+        rows.iter().map(Self::allocation_from_row).collect()
+    }
+}
@@ -0,0 +1,9 @@
+pub mod error;
+pub mod dns;
+pub mod ipam;
+pub mod loadbalancer;
+pub mod peering;
+pub mod policy;
+pub mod vpn;
+
+pub use error::{NetworkError, NetworkResult};
@@ -0,0 +1,726 @@
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::CertifiedKey;
+use rustls::{PrivateKey, ServerConfig};
+use serde::{Deserialize, Serialize};
+use sirsi_key_vault::error::{KeyVaultError, KeyVaultResult};
+use sirsi_key_vault::secret::{CertificateSecret, RotationEvent, Secret, SecretManager, SecretValue};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::error::{NetworkError, NetworkResult};
+use super::rules::wildcard_matches;
+use super::{Certificate, Listener, ListenerProtocol};
+
+pub const DEFAULT_RENEW_BEFORE_DAYS: i64 = 30;
+
+#[derive(Debug, Clone)]
+pub struct IssuedCertificate {
+    pub material: CertificateSecret,
+    pub not_after: DateTime<Utc>,
+}
+
+// Issues listener certificates that are referenced but not yet in the vault, and renews them.
+// Backed by an ACME client or an internal CA.
+#[async_trait]
+pub trait CertificateIssuer: Send + Sync {
+    async fn issue(&self, certificate_id: &str, domains: &[String]) -> NetworkResult<IssuedCertificate>;
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RenewalStatus {
+    Valid,
+    Due,
+    Renewed { at: DateTime<Utc> },
+    Failed { at: DateTime<Utc>, error: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListenerCertificateStatus {
+    pub listener_id: String,
+    pub certificate_id: String,
+    pub version: i32,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub is_default: bool,
+    pub renewal: RenewalStatus,
+}
+
+struct ServedCertificate {
+    domains: Vec<String>,
+    is_default: bool,
+    key: Arc<CertifiedKey>,
+}
+
+// Picks the listener certificate per handshake. Swapping `served` only affects handshakes that
+// start afterwards, so established connections keep the certificate they negotiated.
+#[derive(Default)]
+pub struct ListenerCertResolver {
+    served: RwLock<Vec<ServedCertificate>>,
+}
+
+impl ResolvesServerCert for ListenerCertResolver {
+    fn resolve(&self, client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        let served = self.served.read().expect("listener certificate lock poisoned");
+        let name = client_hello.server_name().map(str::to_ascii_lowercase);
+        name.and_then(|name| served.iter().find(|c| c.domains.iter().any(|d| wildcard_matches(d, &name))))
+            .or_else(|| served.iter().find(|c| c.is_default))
+            .or_else(|| served.first())
+            .map(|c| c.key.clone())
+    }
+}
+
+struct LoadedCertificate {
+    version: i32,
+    expires_at: Option<DateTime<Utc>>,
+    domains: Vec<String>,
+    key: Arc<CertifiedKey>,
+}
+
+struct AttachedListener {
+    certificates: Vec<Certificate>,
+    resolver: Arc<ListenerCertResolver>,
+}
+
+type References = Arc<RwLock<HashMap<String, BTreeSet<String>>>>;
+
+fn cert_error(certificate_id: &str, msg: impl std::fmt::Display) -> NetworkError {
+    NetworkError::Config(format!("Listener certificate {}: {}", certificate_id, msg))
+}
+
+fn vault_error(certificate_id: &str, e: KeyVaultError) -> NetworkError {
+    match e {
+        KeyVaultError::NotFound(msg) => NetworkError::NotFound(msg),
+        other => cert_error(certificate_id, other),
+    }
+}
+
+fn certified_key(certificate_id: &str, material: &CertificateSecret) -> NetworkResult<Arc<CertifiedKey>> {
+    let mut chain = Vec::new();
+    for pem in std::iter::once(&material.certificate).chain(material.chain.iter().flatten()) {
+        let certs = rustls_pemfile::certs(&mut pem.as_bytes()).map_err(|e| cert_error(certificate_id, e))?;
+        chain.extend(certs.into_iter().map(rustls::Certificate));
+    }
+    if chain.is_empty() {
+        return Err(cert_error(certificate_id, "no certificates found in PEM"));
+    }
+    let key = rustls_pemfile::read_all(&mut material.private_key.as_bytes())
+        .map_err(|e| cert_error(certificate_id, e))?
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::PKCS8Key(key) | rustls_pemfile::Item::RSAKey(key) | rustls_pemfile::Item::ECKey(key) => {
+                Some(PrivateKey(key))
+            }
+            _ => None,
+        })
+        .ok_or_else(|| cert_error(certificate_id, "no private key found in PEM"))?;
+    let signing_key = rustls::sign::any_supported_type(&key).map_err(|e| cert_error(certificate_id, e))?;
+    Ok(Arc::new(CertifiedKey::new(chain, signing_key)))
+}
+
+fn vault_certificates(listener: &Listener) -> Vec<Certificate> {
+    if !matches!(listener.protocol, ListenerProtocol::HTTPS | ListenerProtocol::TLS) {
+        return Vec::new();
+    }
+    listener
+        .certificates
+        .iter()
+        .flatten()
+        .filter(|c| c.key_vault_id.is_some())
+        .cloned()
+        .collect()
+}
+
+fn key_vault_id(certificate: &Certificate) -> &str {
+    certificate.key_vault_id.as_deref().expect("only key-vault certificates are attached")
+}
+
+// Listener TLS backed by key-vault certificate secrets. Missing certificates are issued on first
+// attach, renewals are written back to the vault as new secret versions, and every listener
+// using a certificate picks up the new version on the next reload.
+pub struct ListenerCertificates {
+    secrets: Arc<dyn SecretManager>,
+    issuer: Option<Arc<dyn CertificateIssuer>>,
+    renew_before: chrono::Duration,
+    loaded: RwLock<HashMap<String, LoadedCertificate>>,
+    listeners: RwLock<HashMap<String, AttachedListener>>,
+    renewals: RwLock<HashMap<String, RenewalStatus>>,
+    references: References,
+}
+
+impl ListenerCertificates {
+    pub fn new(secrets: Arc<dyn SecretManager>) -> Self {
+        Self {
+            secrets,
+            issuer: None,
+            renew_before: chrono::Duration::days(DEFAULT_RENEW_BEFORE_DAYS),
+            loaded: RwLock::new(HashMap::new()),
+            listeners: RwLock::new(HashMap::new()),
+            renewals: RwLock::new(HashMap::new()),
+            references: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    pub fn with_issuer(mut self, issuer: Arc<dyn CertificateIssuer>) -> Self {
+        self.issuer = Some(issuer);
+        self
+    }
+
+    pub fn with_renew_before(mut self, renew_before: chrono::Duration) -> Self {
+        self.renew_before = renew_before;
+        self
+    }
+
+    pub fn manages(listener: &Listener) -> bool {
+        !vault_certificates(listener).is_empty()
+    }
+
+    async fn issue(&self, certificate_id: &str, domains: &[String]) -> NetworkResult<Secret> {
+        let issuer = self
+            .issuer
+            .as_ref()
+            .ok_or_else(|| cert_error(certificate_id, "not in the vault and no issuer is configured"))?;
+        let issued = issuer.issue(certificate_id, domains).await?;
+        let now = Utc::now();
+        let secret = Secret {
+            id: certificate_id.to_string(),
+            name: certificate_id.to_string(),
+            description: Some(format!("Listener certificate for {}", domains.join(", "))),
+            value: SecretValue::Certificate(issued.material),
+            version: 0,
+            created_at: now,
+            updated_at: now,
+            expires_at: Some(issued.not_after),
+            metadata: HashMap::from([("domains".to_string(), domains.join(","))]),
+            labels: HashMap::new(),
+            rotation_policy: None,
+        };
+        match self.secrets.create_secret(secret).await {
+            Ok(created) => {
+                info!("Issued listener certificate {} for {}", certificate_id, domains.join(", "));
+                Ok(created)
+            }
+            // Another listener issued it first
+            Err(KeyVaultError::Conflict(_)) => self.secrets.get_secret(certificate_id).await.map_err(|e| vault_error(certificate_id, e)),
+            Err(e) => Err(vault_error(certificate_id, e)),
+        }
+    }
+
+    async fn load(&self, certificate: &Certificate) -> NetworkResult<()> {
+        let id = key_vault_id(certificate);
+        if let Some(loaded) = self.loaded.write().expect("listener certificate lock poisoned").get_mut(id) {
+            if loaded.domains.is_empty() {
+                loaded.domains = certificate.domains.clone();
+            }
+            return Ok(());
+        }
+        let secret = match self.secrets.get_secret(id).await {
+            Ok(secret) => secret,
+            Err(KeyVaultError::NotFound(_)) => self.issue(id, &certificate.domains).await?,
+            Err(e) => return Err(vault_error(id, e)),
+        };
+        let loaded = Self::build(id, &secret, certificate.domains.clone())?;
+        self.loaded
+            .write()
+            .expect("listener certificate lock poisoned")
+            .entry(id.to_string())
+            .or_insert(loaded);
+        Ok(())
+    }
+
+    fn build(certificate_id: &str, secret: &Secret, domains: Vec<String>) -> NetworkResult<LoadedCertificate> {
+        let SecretValue::Certificate(material) = &secret.value else {
+            return Err(cert_error(certificate_id, format!("secret {} is not a certificate", secret.id)));
+        };
+        Ok(LoadedCertificate {
+            version: secret.version,
+            expires_at: secret.expires_at,
+            domains,
+            key: certified_key(certificate_id, material)?,
+        })
+    }
+
+    fn publish(&self, attached: &AttachedListener) {
+        let loaded = self.loaded.read().expect("listener certificate lock poisoned");
+        let served = attached
+            .certificates
+            .iter()
+            .filter_map(|c| {
+                let cert = loaded.get(key_vault_id(c))?;
+                let domains = if c.domains.is_empty() { &cert.domains } else { &c.domains };
+                Some(ServedCertificate {
+                    domains: domains.iter().map(|d| d.to_ascii_lowercase()).collect(),
+                    is_default: c.is_default,
+                    key: cert.key.clone(),
+                })
+            })
+            .collect();
+        *attached.resolver.served.write().expect("listener certificate lock poisoned") = served;
+    }
+
+    // Loads (issuing if needed) every key-vault certificate on the listener and returns the
+    // server config its TLS acceptor should use
+    pub async fn attach_listener(&self, listener: &Listener) -> NetworkResult<Arc<ServerConfig>> {
+        let certificates = vault_certificates(listener);
+        if certificates.is_empty() {
+            return Err(NetworkError::Validation(format!(
+                "Listener {} has no key-vault certificates to serve",
+                listener.id
+            )));
+        }
+        for certificate in &certificates {
+            self.load(certificate).await?;
+        }
+
+        let resolver = self
+            .listeners
+            .read()
+            .expect("listener certificate lock poisoned")
+            .get(&listener.id)
+            .map(|attached| attached.resolver.clone())
+            .unwrap_or_default();
+        let attached = AttachedListener { certificates, resolver: resolver.clone() };
+        self.publish(&attached);
+        let mut references = self.references.write().expect("listener certificate lock poisoned");
+        for refs in references.values_mut() {
+            refs.remove(&listener.id);
+        }
+        for certificate in &attached.certificates {
+            references.entry(key_vault_id(certificate).to_string()).or_default().insert(listener.id.clone());
+        }
+        references.retain(|_, refs| !refs.is_empty());
+        drop(references);
+        self.listeners
+            .write()
+            .expect("listener certificate lock poisoned")
+            .insert(listener.id.clone(), attached);
+
+        let mut config = ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_cert_resolver(resolver);
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+        Ok(Arc::new(config))
+    }
+
+    pub fn detach_listener(&self, listener_id: &str) {
+        self.listeners.write().expect("listener certificate lock poisoned").remove(listener_id);
+        let mut references = self.references.write().expect("listener certificate lock poisoned");
+        for refs in references.values_mut() {
+            refs.remove(listener_id);
+        }
+        references.retain(|_, refs| !refs.is_empty());
+        let referenced: BTreeSet<String> = references.keys().cloned().collect();
+        drop(references);
+        self.loaded
+            .write()
+            .expect("listener certificate lock poisoned")
+            .retain(|id, _| referenced.contains(id));
+    }
+
+    fn referencing(&self, certificate_id: &str) -> Vec<String> {
+        self.references
+            .read()
+            .expect("listener certificate lock poisoned")
+            .get(certificate_id)
+            .map(|refs| refs.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    // Swaps in certificates whose vault secret has a newer version; returns the listeners updated
+    pub async fn reload_rotated(&self) -> NetworkResult<Vec<String>> {
+        let versions: Vec<(String, i32)> = self
+            .loaded
+            .read()
+            .expect("listener certificate lock poisoned")
+            .iter()
+            .map(|(id, cert)| (id.clone(), cert.version))
+            .collect();
+        let mut reloaded = BTreeSet::new();
+        for (id, version) in versions {
+            let secret = match self.secrets.get_secret(&id).await {
+                Ok(secret) if secret.version != version => secret,
+                Ok(_) => continue,
+                Err(e) => {
+                    warn!("Failed to check listener certificate {}: {}", id, e);
+                    continue;
+                }
+            };
+            let mut loaded = self.loaded.write().expect("listener certificate lock poisoned");
+            let Some(current) = loaded.get_mut(&id) else { continue };
+            match Self::build(&id, &secret, current.domains.clone()) {
+                Ok(rebuilt) => *current = rebuilt,
+                Err(e) => {
+                    warn!("Keeping version {} of listener certificate {}: {}", version, id, e);
+                    continue;
+                }
+            }
+            drop(loaded);
+            info!("Reloaded listener certificate {} at version {}", id, secret.version);
+            reloaded.extend(self.referencing(&id));
+        }
+
+        let listeners = self.listeners.read().expect("listener certificate lock poisoned");
+        for listener_id in &reloaded {
+            if let Some(attached) = listeners.get(listener_id) {
+                self.publish(attached);
+            }
+        }
+        Ok(reloaded.into_iter().collect())
+    }
+
+    // Re-issues certificates expiring within the renewal window, then reloads every listener
+    // using them. Returns the listeners that now serve a renewed certificate.
+    pub async fn renew_expiring(&self) -> NetworkResult<Vec<String>> {
+        let horizon = Utc::now() + self.renew_before;
+        let due: Vec<(String, Vec<String>)> = self
+            .loaded
+            .read()
+            .expect("listener certificate lock poisoned")
+            .iter()
+            .filter(|(_, cert)| cert.expires_at.is_some_and(|expiry| expiry <= horizon))
+            .map(|(id, cert)| (id.clone(), cert.domains.clone()))
+            .collect();
+        let Some(issuer) = &self.issuer else {
+            for (id, _) in &due {
+                warn!("Listener certificate {} is due for renewal but no issuer is configured", id);
+            }
+            return Ok(Vec::new());
+        };
+
+        for (id, domains) in due {
+            let status = match self.renew(issuer.as_ref(), &id, &domains).await {
+                Ok(()) => RenewalStatus::Renewed { at: Utc::now() },
+                Err(e) => {
+                    warn!("Failed to renew listener certificate {}: {}", id, e);
+                    RenewalStatus::Failed { at: Utc::now(), error: e.to_string() }
+                }
+            };
+            self.renewals.write().expect("listener certificate lock poisoned").insert(id, status);
+        }
+        self.reload_rotated().await
+    }
+
+    async fn renew(&self, issuer: &dyn CertificateIssuer, certificate_id: &str, domains: &[String]) -> NetworkResult<()> {
+        let current = self.secrets.get_secret(certificate_id).await.map_err(|e| vault_error(certificate_id, e))?;
+        let issued = issuer.issue(certificate_id, domains).await?;
+        // Refuse material the listeners could not load before it becomes the current version
+        certified_key(certificate_id, &issued.material)?;
+        let renewed = Secret {
+            value: SecretValue::Certificate(issued.material),
+            expires_at: Some(issued.not_after),
+            ..current
+        };
+        self.secrets.update_secret(renewed).await.map_err(|e| vault_error(certificate_id, e))?;
+        Ok(())
+    }
+
+    pub fn spawn_renewer(self: Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = self.renew_expiring().await {
+                    warn!("Listener certificate renewal failed: {}", e);
+                }
+            }
+        })
+    }
+
+    pub fn list_listener_certificates(&self) -> Vec<ListenerCertificateStatus> {
+        let horizon = Utc::now() + self.renew_before;
+        let listeners = self.listeners.read().expect("listener certificate lock poisoned");
+        let loaded = self.loaded.read().expect("listener certificate lock poisoned");
+        let renewals = self.renewals.read().expect("listener certificate lock poisoned");
+        let mut report: Vec<ListenerCertificateStatus> = listeners
+            .iter()
+            .flat_map(|(listener_id, attached)| attached.certificates.iter().map(move |c| (listener_id, c)))
+            .filter_map(|(listener_id, certificate)| {
+                let id = key_vault_id(certificate);
+                let cert = loaded.get(id)?;
+                let due = cert.expires_at.is_some_and(|expiry| expiry <= horizon);
+                let renewal = match renewals.get(id) {
+                    // A renewal that did not move the expiry out of the window is still due
+                    Some(RenewalStatus::Renewed { .. }) if due => RenewalStatus::Due,
+                    Some(status) => status.clone(),
+                    None if due => RenewalStatus::Due,
+                    None => RenewalStatus::Valid,
+                };
+                Some(ListenerCertificateStatus {
+                    listener_id: listener_id.clone(),
+                    certificate_id: id.to_string(),
+                    version: cert.version,
+                    expires_at: cert.expires_at,
+                    is_default: certificate.is_default,
+                    renewal,
+                })
+            })
+            .collect();
+        report.sort_by(|a, b| (&a.listener_id, &a.certificate_id).cmp(&(&b.listener_id, &b.certificate_id)));
+        report
+    }
+
+    // The vault as other callers should see it: deleting a certificate that a listener still
+    // serves is refused
+    pub fn guarded_secrets(&self) -> Arc<dyn SecretManager> {
+        Arc::new(GuardedSecrets {
+            inner: self.secrets.clone(),
+            references: self.references.clone(),
+        })
+    }
+}
+
+struct GuardedSecrets {
+    inner: Arc<dyn SecretManager>,
+    references: References,
+}
+
+#[async_trait]
+impl SecretManager for GuardedSecrets {
+    async fn create_secret(&self, secret: Secret) -> KeyVaultResult<Secret> {
+        self.inner.create_secret(secret).await
+    }
+
+    async fn get_secret(&self, id: &str) -> KeyVaultResult<Secret> {
+        self.inner.get_secret(id).await
+    }
+
+    async fn get_secret_version(&self, id: &str, version: i32) -> KeyVaultResult<Secret> {
+        self.inner.get_secret_version(id, version).await
+    }
+
+    async fn update_secret(&self, secret: Secret) -> KeyVaultResult<Secret> {
+        self.inner.update_secret(secret).await
+    }
+
+    async fn delete_secret(&self, id: &str) -> KeyVaultResult<()> {
+        let listeners: Vec<String> = self
+            .references
+            .read()
+            .expect("listener certificate lock poisoned")
+            .get(id)
+            .map(|refs| refs.iter().cloned().collect())
+            .unwrap_or_default();
+        if !listeners.is_empty() {
+            return Err(KeyVaultError::Conflict(format!(
+                "Certificate {} is in use by listeners {}",
+                id,
+                listeners.join(", ")
+            )));
+        }
+        self.inner.delete_secret(id).await
+    }
+
+    async fn list_secrets(&self) -> KeyVaultResult<Vec<Secret>> {
+        self.inner.list_secrets().await
+    }
+
+    async fn rotate_secret(&self, id: &str) -> KeyVaultResult<RotationEvent> {
+        self.inner.rotate_secret(id).await
+    }
+
+    async fn get_rotation_history(&self, id: &str) -> KeyVaultResult<Vec<RotationEvent>> {
+        self.inner.get_rotation_history(id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loadbalancer::{
+        HealthCheck, IpAddressType, ListenerAction, LoadBalancer, LoadBalancerManager, LoadBalancerScheme,
+        LoadBalancerStatus, LoadBalancerType, SoftwareLoadBalancer,
+    };
+    use rcgen::{BasicConstraints, CertificateParams, IsCa};
+    use rustls::{ClientConfig, RootCertStore, ServerName};
+    use sirsi_key_vault::secret::InMemorySecretManager;
+    use std::sync::Mutex;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio_rustls::client::TlsStream;
+    use tokio_rustls::TlsConnector;
+
+    // Internal CA that issues a short-lived certificate first and long-lived ones after
+    struct TestCa {
+        ca: rcgen::Certificate,
+        issued: Mutex<Vec<String>>,
+    }
+
+    impl TestCa {
+        fn new() -> Self {
+            let mut params = CertificateParams::new(vec![]);
+            params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+            Self {
+                ca: rcgen::Certificate::from_params(params).unwrap(),
+                issued: Mutex::new(Vec::new()),
+            }
+        }
+
+        fn issued_der(&self, index: usize) -> Vec<u8> {
+            let pem = self.issued.lock().unwrap()[index].clone();
+            rustls_pemfile::certs(&mut pem.as_bytes()).unwrap().remove(0)
+        }
+
+        fn client(&self) -> TlsConnector {
+            let mut roots = RootCertStore::empty();
+            roots.add(&rustls::Certificate(self.ca.serialize_der().unwrap())).unwrap();
+            let config = ClientConfig::builder()
+                .with_safe_defaults()
+                .with_root_certificates(roots)
+                .with_no_client_auth();
+            TlsConnector::from(Arc::new(config))
+        }
+    }
+
+    #[async_trait]
+    impl CertificateIssuer for TestCa {
+        async fn issue(&self, _certificate_id: &str, domains: &[String]) -> NetworkResult<IssuedCertificate> {
+            let cert = rcgen::Certificate::from_params(CertificateParams::new(domains.to_vec())).unwrap();
+            let pem = cert.serialize_pem_with_signer(&self.ca).unwrap();
+            let mut issued = self.issued.lock().unwrap();
+            let lifetime = if issued.is_empty() { chrono::Duration::days(5) } else { chrono::Duration::days(90) };
+            issued.push(pem.clone());
+            Ok(IssuedCertificate {
+                material: CertificateSecret {
+                    certificate: pem,
+                    private_key: cert.serialize_private_key_pem(),
+                    chain: None,
+                },
+                not_after: Utc::now() + lifetime,
+            })
+        }
+    }
+
+    fn https_lb(certificate_id: &str) -> LoadBalancer {
+        let health_check = HealthCheck {
+            protocol: ListenerProtocol::HTTP,
+            port: None,
+            path: Some("/health".into()),
+            interval_seconds: 10,
+            timeout_seconds: 5,
+            healthy_threshold: 2,
+            unhealthy_threshold: 2,
+        };
+        LoadBalancer {
+            id: "lb-1".into(),
+            name: "edge".into(),
+            lb_type: LoadBalancerType::Application,
+            status: LoadBalancerStatus::Active,
+            scheme: LoadBalancerScheme::Internet,
+            ip_address_type: IpAddressType::IPv4,
+            subnets: vec![],
+            security_groups: vec![],
+            listeners: vec![Listener {
+                id: "https".into(),
+                protocol: ListenerProtocol::HTTPS,
+                port: 443,
+                ssl_policy: None,
+                certificates: Some(vec![Certificate {
+                    arn: String::new(),
+                    is_default: true,
+                    key_vault_id: Some(certificate_id.into()),
+                    domains: vec!["app.example.com".into()],
+                }]),
+                default_action: ListenerAction::FixedResponse {
+                    content_type: "text/plain".into(),
+                    message: "ok".into(),
+                    status_code: 200,
+                },
+                rules: vec![],
+            }],
+            health_check,
+            tags: HashMap::new(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    async fn connect(ca: &TestCa, port: u16) -> TlsStream<TcpStream> {
+        let tcp = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        ca.client()
+            .connect(ServerName::try_from("app.example.com").unwrap(), tcp)
+            .await
+            .unwrap()
+    }
+
+    fn served_der(stream: &TlsStream<TcpStream>) -> Vec<u8> {
+        stream.get_ref().1.peer_certificates().unwrap()[0].0.clone()
+    }
+
+    async fn get(stream: &mut TlsStream<TcpStream>) -> String {
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: app.example.com\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = Vec::new();
+        let mut chunk = [0u8; 1024];
+        while !response.ends_with(b"\r\n\r\nok") {
+            let n = stream.read(&mut chunk).await.unwrap();
+            assert!(n > 0, "connection closed mid-response");
+            response.extend_from_slice(&chunk[..n]);
+        }
+        String::from_utf8(response).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_renewal_reaches_new_connections_without_dropping_open_ones() {
+        let ca = Arc::new(TestCa::new());
+        let secrets = Arc::new(InMemorySecretManager::new());
+        let certificates = Arc::new(ListenerCertificates::new(secrets.clone()).with_issuer(ca.clone()));
+        let lb = Arc::new(SoftwareLoadBalancer::new().with_listener_certificates(certificates.clone()));
+        lb.create_load_balancer(https_lb("edge-cert")).await.unwrap();
+        assert_eq!(secrets.get_secret("edge-cert").await.unwrap().version, 1);
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(lb.clone().serve_tls("https".into(), listener));
+
+        let mut open = connect(&ca, port).await;
+        assert!(get(&mut open).await.starts_with("HTTP/1.1 200 OK"));
+        assert_eq!(served_der(&open), ca.issued_der(0));
+        let report = certificates.list_listener_certificates();
+        assert_eq!(report.len(), 1);
+        assert_eq!(report[0].renewal, RenewalStatus::Due);
+
+        assert_eq!(certificates.renew_expiring().await.unwrap(), vec!["https".to_string()]);
+        assert_eq!(secrets.get_secret("edge-cert").await.unwrap().version, 2);
+
+        // The open connection keeps working on the certificate it negotiated
+        assert!(get(&mut open).await.starts_with("HTTP/1.1 200 OK"));
+        assert_eq!(served_der(&open), ca.issued_der(0));
+        let mut fresh = connect(&ca, port).await;
+        assert!(get(&mut fresh).await.starts_with("HTTP/1.1 200 OK"));
+        assert_eq!(served_der(&fresh), ca.issued_der(1));
+
+        let report = certificates.list_listener_certificates();
+        assert_eq!(report[0].version, 2);
+        assert!(matches!(report[0].renewal, RenewalStatus::Renewed { .. }));
+        assert!(report[0].expires_at.unwrap() > Utc::now() + chrono::Duration::days(60));
+    }
+
+    #[tokio::test]
+    async fn test_referenced_certificate_cannot_be_deleted() {
+        let ca = Arc::new(TestCa::new());
+        let secrets = Arc::new(InMemorySecretManager::new());
+
+        // Without an issuer a missing certificate fails listener creation
+        let unissued = Arc::new(ListenerCertificates::new(secrets.clone()));
+        let lb = SoftwareLoadBalancer::new().with_listener_certificates(unissued.clone());
+        assert!(matches!(lb.create_load_balancer(https_lb("edge-cert")).await, Err(NetworkError::Config(_))));
+        assert!(unissued.list_listener_certificates().is_empty());
+
+        let certificates = Arc::new(ListenerCertificates::new(secrets.clone()).with_issuer(ca));
+        let lb = SoftwareLoadBalancer::new().with_listener_certificates(certificates.clone());
+        lb.create_load_balancer(https_lb("edge-cert")).await.unwrap();
+        let vault = certificates.guarded_secrets();
+        assert!(matches!(vault.delete_secret("edge-cert").await, Err(KeyVaultError::Conflict(_))));
+
+        lb.delete_load_balancer("lb-1").await.unwrap();
+        assert!(certificates.list_listener_certificates().is_empty());
+        vault.delete_secret("edge-cert").await.unwrap();
+    }
+}
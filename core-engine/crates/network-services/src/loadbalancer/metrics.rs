@@ -0,0 +1,274 @@
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicI64, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, DurationRound, Utc};
+
+use super::LoadBalancerMetrics;
+
+const SHARDS: usize = 16;
+// One day of per-minute samples
+pub const DEFAULT_RETENTION_MINUTES: usize = 24 * 60;
+
+static NEXT_SHARD: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static SHARD: usize = NEXT_SHARD.fetch_add(1, Ordering::Relaxed) % SHARDS;
+}
+
+// Padded to a cache line so threads bumping neighbouring shards don't contend
+#[derive(Default)]
+#[repr(align(64))]
+struct Shard(AtomicU64);
+
+// Each thread increments its own shard; reads sum all shards
+#[derive(Default)]
+pub struct ShardedCounter {
+    shards: [Shard; SHARDS],
+}
+
+impl ShardedCounter {
+    pub fn add(&self, n: u64) {
+        SHARD.with(|shard| self.shards[*shard].0.fetch_add(n, Ordering::Relaxed));
+    }
+
+    pub fn get(&self) -> u64 {
+        self.shards.iter().map(|s| s.0.load(Ordering::Relaxed)).sum()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CounterSnapshot {
+    pub active_connections: i64,
+    pub new_connections: u64,
+    pub processed_bytes: u64,
+    pub request_count: u64,
+    pub http_2xx: u64,
+    pub http_3xx: u64,
+    pub http_4xx: u64,
+    pub http_5xx: u64,
+}
+
+// Data-path counters for one load balancer; every method is lock-free
+#[derive(Default)]
+pub struct TrafficCounters {
+    active_connections: AtomicI64,
+    new_connections: ShardedCounter,
+    processed_bytes: ShardedCounter,
+    request_count: ShardedCounter,
+    // Indexed by status class: 2xx, 3xx, 4xx, 5xx
+    status_classes: [ShardedCounter; 4],
+}
+
+impl TrafficCounters {
+    pub fn open_connection(self: &Arc<Self>) -> ConnectionGuard {
+        self.new_connections.add(1);
+        self.active_connections.fetch_add(1, Ordering::Relaxed);
+        ConnectionGuard {
+            counters: self.clone(),
+        }
+    }
+
+    pub fn record_bytes(&self, bytes: u64) {
+        self.processed_bytes.add(bytes);
+    }
+
+    // Statuses outside 2xx-5xx count as requests but not towards any class
+    pub fn record_request(&self, status: u16) {
+        self.request_count.add(1);
+        if let 200..=599 = status {
+            self.status_classes[(status / 100 - 2) as usize].add(1);
+        }
+    }
+
+    pub fn snapshot(&self) -> CounterSnapshot {
+        CounterSnapshot {
+            active_connections: self.active_connections.load(Ordering::Relaxed),
+            new_connections: self.new_connections.get(),
+            processed_bytes: self.processed_bytes.get(),
+            request_count: self.request_count.get(),
+            http_2xx: self.status_classes[0].get(),
+            http_3xx: self.status_classes[1].get(),
+            http_4xx: self.status_classes[2].get(),
+            http_5xx: self.status_classes[3].get(),
+        }
+    }
+}
+
+// Keeps the active connection gauge honest however the connection ends
+pub struct ConnectionGuard {
+    counters: Arc<TrafficCounters>,
+}
+
+impl ConnectionGuard {
+    pub fn record_bytes(&self, bytes: u64) {
+        self.counters.record_bytes(bytes);
+    }
+
+    pub fn record_request(&self, status: u16) {
+        self.counters.record_request(status);
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.counters.active_connections.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+fn delta(current: u64, previous: u64) -> i64 {
+    current.saturating_sub(previous) as i64
+}
+
+// Per-minute history built from successive counter snapshots. Only the sampler touches it.
+pub struct MinuteHistory {
+    last: CounterSnapshot,
+    samples: VecDeque<LoadBalancerMetrics>,
+    retention: usize,
+}
+
+impl MinuteHistory {
+    pub fn new(retention: usize) -> Self {
+        Self {
+            last: CounterSnapshot::default(),
+            samples: VecDeque::new(),
+            retention,
+        }
+    }
+
+    pub fn sample(
+        &mut self,
+        id: &str,
+        now: DateTime<Utc>,
+        current: CounterSnapshot,
+        healthy_hosts: i32,
+        unhealthy_hosts: i32,
+    ) -> LoadBalancerMetrics {
+        let timestamp = now.duration_trunc(chrono::Duration::minutes(1)).unwrap_or(now);
+        let last = std::mem::replace(&mut self.last, current);
+        let sample = LoadBalancerMetrics {
+            id: id.to_string(),
+            timestamp,
+            active_connections: current.active_connections,
+            new_connections: delta(current.new_connections, last.new_connections),
+            processed_bytes: delta(current.processed_bytes, last.processed_bytes),
+            request_count: delta(current.request_count, last.request_count),
+            healthy_host_count: healthy_hosts,
+            unhealthy_host_count: unhealthy_hosts,
+            http_2xx: delta(current.http_2xx, last.http_2xx),
+            http_3xx: delta(current.http_3xx, last.http_3xx),
+            http_4xx: delta(current.http_4xx, last.http_4xx),
+            http_5xx: delta(current.http_5xx, last.http_5xx),
+        };
+        self.samples.push_back(sample.clone());
+        while self.samples.len() > self.retention {
+            self.samples.pop_front();
+        }
+        sample
+    }
+
+    pub fn since(&self, start: DateTime<Utc>) -> Vec<LoadBalancerMetrics> {
+        self.samples.iter().filter(|s| s.timestamp >= start).cloned().collect()
+    }
+}
+
+pub struct LoadBalancerTelemetry {
+    pub counters: Arc<TrafficCounters>,
+    pub history: Mutex<MinuteHistory>,
+}
+
+impl LoadBalancerTelemetry {
+    pub fn new(retention: usize) -> Self {
+        Self {
+            counters: Arc::new(TrafficCounters::default()),
+            history: Mutex::new(MinuteHistory::new(retention)),
+        }
+    }
+}
+
+pub struct PrometheusSeries<'a> {
+    pub id: &'a str,
+    pub counters: CounterSnapshot,
+    pub healthy_hosts: i32,
+    pub unhealthy_hosts: i32,
+}
+
+// Prometheus text exposition format 0.0.4
+pub fn render_prometheus(series: &[PrometheusSeries<'_>]) -> String {
+    let mut out = String::new();
+    let mut family = |name: &str, kind: &str, help: &str, value: &dyn Fn(&PrometheusSeries<'_>) -> Vec<(String, String)>| {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        for s in series {
+            for (extra_labels, v) in value(s) {
+                let _ = writeln!(out, "{}{{load_balancer=\"{}\"{}}} {}", name, s.id, extra_labels, v);
+            }
+        }
+    };
+
+    family("sirsi_lb_active_connections", "gauge", "Currently open client connections", &|s| {
+        vec![(String::new(), s.counters.active_connections.to_string())]
+    });
+    family("sirsi_lb_connections_total", "counter", "Client connections accepted", &|s| {
+        vec![(String::new(), s.counters.new_connections.to_string())]
+    });
+    family("sirsi_lb_processed_bytes_total", "counter", "Bytes processed in both directions", &|s| {
+        vec![(String::new(), s.counters.processed_bytes.to_string())]
+    });
+    family("sirsi_lb_requests_total", "counter", "Requests processed", &|s| {
+        vec![(String::new(), s.counters.request_count.to_string())]
+    });
+    family("sirsi_lb_responses_total", "counter", "Responses by status class", &|s| {
+        [
+            ("2xx", s.counters.http_2xx),
+            ("3xx", s.counters.http_3xx),
+            ("4xx", s.counters.http_4xx),
+            ("5xx", s.counters.http_5xx),
+        ]
+        .into_iter()
+        .map(|(class, v)| (format!(",class=\"{}\"", class), v.to_string()))
+        .collect()
+    });
+    family("sirsi_lb_healthy_hosts", "gauge", "Healthy targets behind the load balancer", &|s| {
+        vec![(String::new(), s.healthy_hosts.to_string())]
+    });
+    family("sirsi_lb_unhealthy_hosts", "gauge", "Unhealthy targets behind the load balancer", &|s| {
+        vec![(String::new(), s.unhealthy_hosts.to_string())]
+    });
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_classes_and_deltas() {
+        let counters = Arc::new(TrafficCounters::default());
+        {
+            let conn = counters.open_connection();
+            for status in [200, 204, 301, 404, 503, 101] {
+                conn.record_request(status);
+            }
+            conn.record_bytes(512);
+            assert_eq!(counters.snapshot().active_connections, 1);
+        }
+
+        let mut history = MinuteHistory::new(2);
+        let now = Utc::now();
+        let first = history.sample("lb", now, counters.snapshot(), 3, 1);
+        assert_eq!(first.request_count, 6);
+        assert_eq!((first.http_2xx, first.http_3xx, first.http_4xx, first.http_5xx), (2, 1, 1, 1));
+        assert_eq!(first.active_connections, 0);
+        assert_eq!(first.timestamp.timestamp() % 60, 0);
+
+        counters.record_request(500);
+        let second = history.sample("lb", now, counters.snapshot(), 3, 1);
+        assert_eq!(second.request_count, 1);
+        assert_eq!(second.new_connections, 0);
+
+        history.sample("lb", now, counters.snapshot(), 3, 1);
+        assert_eq!(history.since(now - chrono::Duration::hours(1)).len(), 2);
+    }
+}
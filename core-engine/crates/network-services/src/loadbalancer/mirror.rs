@@ -0,0 +1,433 @@
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use rand::Rng;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::error::{NetworkError, NetworkResult};
+use super::rules::RequestInfo;
+use super::TrafficMirror;
+
+// Same marker Envoy appends, so shadow traffic is recognisable in the target's access logs
+pub const SHADOW_HOST_SUFFIX: &str = "-shadow";
+
+const MAX_HEADER_BYTES: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Default)]
+pub struct HttpRequest {
+    pub method: String,
+    // Includes the query string
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    pub source_ip: Option<IpAddr>,
+}
+
+impl HttpRequest {
+    pub fn new(method: impl Into<String>, path: impl Into<String>) -> Self {
+        Self {
+            method: method.into(),
+            path: path.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn with_body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        find_header(&self.headers, name)
+    }
+
+    // What the rule table matches on; the Host header's port is not part of the host
+    pub fn request_info(&self) -> RequestInfo {
+        let host = self.header("host").unwrap_or_default();
+        let (host, _) = split_port(host);
+        let (path, query) = self.path.split_once('?').unwrap_or((&self.path, ""));
+        let mut info = RequestInfo::new(host, path);
+        for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            info = info.with_query(key, value);
+        }
+        for (name, value) in &self.headers {
+            info = info.with_header(name, value);
+        }
+        if let Some(ip) = self.source_ip {
+            info = info.with_source_ip(ip);
+        }
+        info
+    }
+
+    // One request per upstream connection, so framing headers are always rewritten
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut head = format!("{} {} HTTP/1.1\r\n", self.method, self.path);
+        for (name, value) in self.headers.iter().filter(|(name, _)| !is_framing_header(name)) {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str(&format!("Content-Length: {}\r\nConnection: close\r\n\r\n", self.body.len()));
+        let mut bytes = head.into_bytes();
+        bytes.extend_from_slice(&self.body);
+        bytes
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    pub fn new(status: u16, content_type: &str, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status,
+            headers: vec![("Content-Type".to_string(), content_type.to_string())],
+            body: body.into(),
+        }
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        find_header(&self.headers, name)
+    }
+
+    // Client-facing responses keep the connection open for the next request
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut head = format!("HTTP/1.1 {} {}\r\n", self.status, reason_phrase(self.status));
+        for (name, value) in self.headers.iter().filter(|(name, _)| !is_framing_header(name)) {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str(&format!("Content-Length: {}\r\n\r\n", self.body.len()));
+        let mut bytes = head.into_bytes();
+        bytes.extend_from_slice(&self.body);
+        bytes
+    }
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        301 => "Moved Permanently",
+        302 => "Found",
+        400 => "Bad Request",
+        404 => "Not Found",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        504 => "Gateway Timeout",
+        _ => "",
+    }
+}
+
+fn find_header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(header, _)| header.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+fn is_framing_header(name: &str) -> bool {
+    ["content-length", "connection", "transfer-encoding", "keep-alive"]
+        .iter()
+        .any(|framing| framing.eq_ignore_ascii_case(name))
+}
+
+// Separates a trailing `:port`, leaving bracketed IPv6 literals intact
+fn split_port(host: &str) -> (&str, Option<&str>) {
+    match host.rsplit_once(':') {
+        Some((name, port)) if !name.is_empty() && port.parse::<u16>().is_ok() && !name.ends_with(':') => {
+            (name, Some(port))
+        }
+        _ => (host, None),
+    }
+}
+
+fn upstream_error(e: std::io::Error) -> NetworkError {
+    NetworkError::LoadBalancer(format!("Upstream exchange failed: {}", e))
+}
+
+fn malformed(reason: &str) -> NetworkError {
+    NetworkError::LoadBalancer(format!("Malformed upstream response: {}", reason))
+}
+
+// Bodies are framed by Content-Length, or by the upstream closing the connection
+pub(crate) async fn read_response<S: AsyncRead + Unpin>(stream: &mut S) -> NetworkResult<HttpResponse> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 8192];
+    let head_end = loop {
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos;
+        }
+        if buf.len() > MAX_HEADER_BYTES {
+            return Err(malformed("headers too large"));
+        }
+        let n = stream.read(&mut chunk).await.map_err(upstream_error)?;
+        if n == 0 {
+            return Err(malformed("connection closed before the headers ended"));
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    };
+
+    let head = std::str::from_utf8(&buf[..head_end]).map_err(|_| malformed("headers are not UTF-8"))?;
+    let mut lines = head.split("\r\n");
+    let status = lines
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| malformed("bad status line"))?;
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .collect();
+    let length = find_header(&headers, "content-length").and_then(|v| v.parse::<usize>().ok());
+
+    let mut body = buf[head_end + 4..].to_vec();
+    while length.is_none_or(|len| body.len() < len) {
+        let n = stream.read(&mut chunk).await.map_err(upstream_error)?;
+        if n == 0 {
+            if length.is_some() {
+                return Err(malformed("body shorter than Content-Length"));
+            }
+            break;
+        }
+        body.extend_from_slice(&chunk[..n]);
+    }
+    if let Some(len) = length {
+        body.truncate(len);
+    }
+    Ok(HttpResponse { status, headers, body })
+}
+
+fn malformed_request(reason: &str) -> NetworkError {
+    NetworkError::Validation(format!("Malformed client request: {}", reason))
+}
+
+// Reads the next request on a client connection; `buf` carries bytes read past the previous
+// one. Returns None when the client closes the connection between requests.
+pub(crate) async fn read_request<S: AsyncRead + Unpin>(stream: &mut S, buf: &mut Vec<u8>) -> NetworkResult<Option<HttpRequest>> {
+    let mut chunk = [0u8; 8192];
+    let head_end = loop {
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos;
+        }
+        if buf.len() > MAX_HEADER_BYTES {
+            return Err(malformed_request("headers too large"));
+        }
+        let n = stream.read(&mut chunk).await.map_err(upstream_error)?;
+        if n == 0 {
+            return if buf.is_empty() { Ok(None) } else { Err(malformed_request("connection closed mid-request")) };
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    };
+
+    let head = std::str::from_utf8(&buf[..head_end]).map_err(|_| malformed_request("headers are not UTF-8"))?;
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    let (Some(method), Some(path)) = (request_line.next(), request_line.next()) else {
+        return Err(malformed_request("bad request line"));
+    };
+    let mut request = HttpRequest::new(method, path);
+    request.headers = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .collect();
+    let length = match request.header("content-length") {
+        Some(value) => value.parse::<usize>().map_err(|_| malformed_request("bad Content-Length"))?,
+        None => 0,
+    };
+
+    buf.drain(..head_end + 4);
+    while buf.len() < length {
+        let n = stream.read(&mut chunk).await.map_err(upstream_error)?;
+        if n == 0 {
+            return Err(malformed_request("body shorter than Content-Length"));
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+    request.body = buf.drain(..length).collect();
+    Ok(Some(request))
+}
+
+pub(crate) async fn exchange<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    request: &HttpRequest,
+) -> NetworkResult<HttpResponse> {
+    stream.write_all(&request.encode()).await.map_err(upstream_error)?;
+    read_response(stream).await
+}
+
+#[derive(Debug, Default)]
+struct MirrorCounters {
+    mirrored: AtomicU64,
+    completed: AtomicU64,
+    errors: AtomicU64,
+    dropped: AtomicU64,
+}
+
+// Shadow traffic is accounted here only, never in the load balancer's TrafficCounters
+#[derive(Debug, Clone, PartialEq)]
+pub struct MirrorMetrics {
+    pub rule_id: String,
+    pub target_group: String,
+    pub mirrored: u64,
+    pub completed: u64,
+    // Connection failures, timeouts and 5xx responses from the shadow target
+    pub errors: u64,
+    // Sampled in but skipped because `max_concurrent` shadow requests were already in flight
+    pub dropped: u64,
+}
+
+impl MirrorMetrics {
+    pub fn error_rate(&self) -> f64 {
+        let finished = self.completed + self.errors;
+        if finished == 0 {
+            0.0
+        } else {
+            self.errors as f64 / finished as f64
+        }
+    }
+}
+
+// Runtime side of a rule's TrafficMirror: the concurrency cap and the shadow-only counters
+#[derive(Debug)]
+pub struct MirrorChannel {
+    rule_id: String,
+    config: TrafficMirror,
+    permits: Arc<Semaphore>,
+    counters: Arc<MirrorCounters>,
+}
+
+impl MirrorChannel {
+    pub fn new(rule_id: &str, config: TrafficMirror) -> Self {
+        Self {
+            rule_id: rule_id.to_string(),
+            permits: Arc::new(Semaphore::new(config.max_concurrent)),
+            config,
+            counters: Arc::default(),
+        }
+    }
+
+    pub fn config(&self) -> &TrafficMirror {
+        &self.config
+    }
+
+    // Carries counters across rule table swaps, and in-flight accounting when the cap is unchanged
+    pub(crate) fn inherit(&mut self, previous: &MirrorChannel) {
+        self.counters = previous.counters.clone();
+        if previous.config.max_concurrent == self.config.max_concurrent {
+            self.permits = previous.permits.clone();
+        }
+    }
+
+    // None when the request is sampled out or the concurrency cap is reached
+    pub(crate) fn admit(&self) -> Option<OwnedSemaphorePermit> {
+        if self.config.percentage < 100.0 && rand::thread_rng().gen_range(0.0..100.0) >= self.config.percentage {
+            return None;
+        }
+        match self.permits.clone().try_acquire_owned() {
+            Ok(permit) => {
+                self.counters.mirrored.fetch_add(1, Ordering::Relaxed);
+                Some(permit)
+            }
+            Err(_) => {
+                self.counters.dropped.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    pub(crate) fn record(&self, success: bool) {
+        let counter = if success { &self.counters.completed } else { &self.counters.errors };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn shadow_request(&self, request: &HttpRequest) -> HttpRequest {
+        let mut shadow = request.clone();
+        shadow
+            .headers
+            .retain(|(name, _)| !self.config.strip_headers.iter().any(|strip| strip.eq_ignore_ascii_case(name)));
+        for (name, value) in shadow.headers.iter_mut() {
+            if name.eq_ignore_ascii_case("host") {
+                *value = match split_port(value) {
+                    (host, Some(port)) => format!("{}{}:{}", host, SHADOW_HOST_SUFFIX, port),
+                    (host, None) => format!("{}{}", host, SHADOW_HOST_SUFFIX),
+                };
+            }
+        }
+        if let Some(tag) = &self.config.tag_header {
+            shadow.headers.retain(|(name, _)| !name.eq_ignore_ascii_case(tag));
+            shadow.headers.push((tag.clone(), "true".to_string()));
+        }
+        shadow
+    }
+
+    pub fn metrics(&self) -> MirrorMetrics {
+        MirrorMetrics {
+            rule_id: self.rule_id.clone(),
+            target_group: self.config.target_group.clone(),
+            mirrored: self.counters.mirrored.load(Ordering::Relaxed),
+            completed: self.counters.completed.load(Ordering::Relaxed),
+            errors: self.counters.errors.load(Ordering::Relaxed),
+            dropped: self.counters.dropped.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mirror(percentage: f64, max_concurrent: usize) -> TrafficMirror {
+        TrafficMirror {
+            target_group: "tg-shadow".to_string(),
+            percentage,
+            max_concurrent,
+            strip_headers: vec!["Authorization".to_string()],
+            tag_header: Some("x-sirsi-mirrored".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_shadow_request_headers() {
+        let channel = MirrorChannel::new("checkout", mirror(100.0, 1));
+        let request = HttpRequest::new("POST", "/cart?id=7")
+            .with_header("Host", "shop.example.com:8080")
+            .with_header("authorization", "Bearer secret")
+            .with_header("x-sirsi-mirrored", "false")
+            .with_body("{}");
+
+        let shadow = channel.shadow_request(&request);
+        assert_eq!(shadow.header("host"), Some("shop.example.com-shadow:8080"));
+        assert_eq!(shadow.header("authorization"), None);
+        assert_eq!(shadow.header("x-sirsi-mirrored"), Some("true"));
+        assert_eq!(shadow.body, b"{}");
+
+        let info = request.request_info();
+        assert_eq!(info.host, "shop.example.com");
+        assert_eq!(info.path, "/cart");
+        assert_eq!(info.query.get("id").map(String::as_str), Some("7"));
+    }
+
+    #[test]
+    fn test_admit_caps_concurrency() {
+        let channel = MirrorChannel::new("checkout", mirror(100.0, 2));
+        let first = channel.admit();
+        let second = channel.admit();
+        assert!(first.is_some() && second.is_some());
+        assert!(channel.admit().is_none());
+        drop(first);
+        assert!(channel.admit().is_some());
+
+        let metrics = channel.metrics();
+        assert_eq!((metrics.mirrored, metrics.dropped), (3, 1));
+        assert!(MirrorChannel::new("never", mirror(0.0, 1)).admit().is_none());
+    }
+}
@@ -5,6 +5,22 @@ use chrono::{DateTime, Utc};
 
 use crate::error::NetworkResult;
 
+pub mod listener_tls;
+pub mod metrics;
+pub mod mirror;
+pub mod rules;
+pub mod software;
+pub mod upstream;
+
+pub use listener_tls::{
+    CertificateIssuer, IssuedCertificate, ListenerCertResolver, ListenerCertificateStatus, ListenerCertificates, RenewalStatus,
+};
+pub use metrics::{ConnectionGuard, TrafficCounters};
+pub use mirror::{HttpRequest, HttpResponse, MirrorMetrics};
+pub use rules::{RequestInfo, RuleTable};
+pub use software::SoftwareLoadBalancer;
+pub use upstream::{TargetTlsReport, UpstreamConnector, UpstreamStream};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LoadBalancer {
     pub id: String,
@@ -75,6 +91,12 @@ pub enum ListenerProtocol {
 pub struct Certificate {
     pub arn: String,
     pub is_default: bool,
+    // Key-vault certificate secret id; the software load balancer serves these itself
+    #[serde(default)]
+    pub key_vault_id: Option<String>,
+    // SNI names the certificate answers; also what gets issued when the secret does not exist
+    #[serde(default)]
+    pub domains: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -90,6 +112,23 @@ pub struct ListenerRule {
     pub priority: i32,
     pub conditions: Vec<RuleCondition>,
     pub action: ListenerAction,
+    #[serde(default)]
+    pub mirror: Option<TrafficMirror>,
+}
+
+// Shadows a share of a forwarding rule's requests to another target group. Shadow responses
+// are discarded and never delay or alter the primary response.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrafficMirror {
+    pub target_group: String,
+    pub percentage: f64,
+    pub max_concurrent: usize,
+    // Removed from the shadow copy, e.g. authorization or cookies
+    #[serde(default)]
+    pub strip_headers: Vec<String>,
+    // Set to "true" on the shadow copy so the target can tell it apart
+    #[serde(default)]
+    pub tag_header: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -122,6 +161,17 @@ pub struct TargetGroup {
     pub vpc_id: String,
     pub health_check: HealthCheck,
     pub attributes: TargetGroupAttributes,
+    #[serde(default)]
+    pub backend_tls: Option<BackendTls>,
+}
+
+// TLS for the LB-to-target hop. The client certificate is a key-vault certificate secret id.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackendTls {
+    pub server_ca_bundle: String,
+    pub client_certificate_secret: Option<String>,
+    pub sni_override: Option<String>,
+    pub verify_hostname: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -191,6 +241,16 @@ pub trait LoadBalancerManager: Send + Sync {
     async fn get_metrics(&self, id: &str, window: chrono::Duration) -> NetworkResult<Vec<LoadBalancerMetrics>>;
 }
 
+// Rule changes are validated against the listener's full resulting rule set. Providers with a
+// bulk priority API (e.g. ELBv2 SetRulePriorities) should map `set_rule_priorities` onto it.
+#[async_trait]
+pub trait ListenerRuleManager: Send + Sync {
+    async fn add_rule(&self, listener_id: &str, rule: ListenerRule) -> NetworkResult<ListenerRule>;
+    async fn remove_rule(&self, listener_id: &str, rule_id: &str) -> NetworkResult<()>;
+    async fn list_rules(&self, listener_id: &str) -> NetworkResult<Vec<ListenerRule>>;
+    async fn set_rule_priorities(&self, listener_id: &str, ordered_rule_ids: Vec<String>) -> NetworkResult<Vec<ListenerRule>>;
+}
+
 #[async_trait]
 pub trait TargetGroupManager: Send + Sync {
     async fn create_target_group(&self, group: TargetGroup) -> NetworkResult<TargetGroup>;
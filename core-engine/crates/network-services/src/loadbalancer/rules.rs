@@ -0,0 +1,432 @@
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use crate::error::{NetworkError, NetworkResult};
+use super::mirror::MirrorChannel;
+use super::{ListenerAction, ListenerRule, RuleCondition};
+
+// Matches the ELBv2 rule priority range
+pub const MIN_RULE_PRIORITY: i32 = 1;
+pub const MAX_RULE_PRIORITY: i32 = 50_000;
+
+#[derive(Debug, Clone, Default)]
+pub struct RequestInfo {
+    pub host: String,
+    pub path: String,
+    pub headers: HashMap<String, String>,
+    pub query: HashMap<String, String>,
+    pub source_ip: Option<IpAddr>,
+}
+
+impl RequestInfo {
+    pub fn new(host: impl Into<String>, path: impl Into<String>) -> Self {
+        Self {
+            host: host.into(),
+            path: path.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.insert(name.into().to_ascii_lowercase(), value.into());
+        self
+    }
+
+    pub fn with_query(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.query.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn with_source_ip(mut self, ip: IpAddr) -> Self {
+        self.source_ip = Some(ip);
+        self
+    }
+}
+
+// `*` matches any run of characters and `?` exactly one, as in ELB path and host patterns
+pub fn wildcard_matches(pattern: &str, value: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let value: Vec<char> = value.chars().collect();
+    let (mut p, mut v) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while v < value.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == value[v]) {
+            p += 1;
+            v += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, v));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            v = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Cidr {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    pub fn parse(value: &str) -> NetworkResult<Self> {
+        let invalid = || NetworkError::Validation(format!("Invalid CIDR block: {}", value));
+        let (address, prefix) = value.split_once('/').unwrap_or((value, ""));
+        let network: IpAddr = address.parse().map_err(|_| invalid())?;
+        let max = if network.is_ipv4() { 32 } else { 128 };
+        let prefix = if prefix.is_empty() { max } else { prefix.parse().map_err(|_| invalid())? };
+        if prefix > max {
+            return Err(invalid());
+        }
+        Ok(Self { network, prefix })
+    }
+
+    pub fn network(&self) -> IpAddr {
+        self.network
+    }
+
+    pub fn prefix(&self) -> u8 {
+        self.prefix
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.network, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+
+    pub fn overlaps(&self, other: &Cidr) -> bool {
+        self.contains(other.network) || other.contains(self.network)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum CompiledCondition {
+    Path(String),
+    Host(String),
+    Header { name: String, values: Vec<String> },
+    Query(Vec<(String, String)>),
+    SourceIp(Vec<Cidr>),
+}
+
+impl CompiledCondition {
+    fn compile(condition: &RuleCondition) -> NetworkResult<Self> {
+        Ok(match condition {
+            RuleCondition::PathPattern(pattern) => Self::Path(pattern.clone()),
+            RuleCondition::HostHeader(pattern) => Self::Host(pattern.to_ascii_lowercase()),
+            RuleCondition::HttpHeader { name, values } => Self::Header {
+                name: name.to_ascii_lowercase(),
+                values: values.iter().map(|v| v.to_ascii_lowercase()).collect(),
+            },
+            RuleCondition::QueryString(pairs) => Self::Query(pairs.iter().map(|(k, v)| (k.clone(), v.clone())).collect()),
+            RuleCondition::SourceIp(blocks) => {
+                Self::SourceIp(blocks.iter().map(|b| Cidr::parse(b)).collect::<NetworkResult<_>>()?)
+            }
+        })
+    }
+
+    fn matches(&self, request: &RequestInfo) -> bool {
+        match self {
+            Self::Path(pattern) => wildcard_matches(pattern, &request.path),
+            Self::Host(pattern) => wildcard_matches(pattern, &request.host.to_ascii_lowercase()),
+            Self::Header { name, values } => request
+                .headers
+                .get(name)
+                .map(|value| {
+                    let value = value.to_ascii_lowercase();
+                    values.iter().any(|pattern| wildcard_matches(pattern, &value))
+                })
+                .unwrap_or(false),
+            Self::Query(pairs) => pairs.iter().all(|(key, pattern)| {
+                request
+                    .query
+                    .get(key)
+                    .map(|value| wildcard_matches(pattern, value))
+                    .unwrap_or(false)
+            }),
+            Self::SourceIp(blocks) => request
+                .source_ip
+                .map(|ip| blocks.iter().any(|block| block.contains(ip)))
+                .unwrap_or(false),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CompiledRule {
+    id: String,
+    conditions: Vec<CompiledCondition>,
+    action: ListenerAction,
+    mirror: Option<Arc<MirrorChannel>>,
+}
+
+// Immutable, priority-ordered snapshot of a listener's rules. Updates build a new table and
+// swap it in whole, so matching never observes a half-applied change.
+#[derive(Debug, Clone)]
+pub struct RuleTable {
+    rules: Vec<CompiledRule>,
+    default_action: ListenerAction,
+}
+
+impl RuleTable {
+    pub fn compile(rules: &[ListenerRule], default_action: &ListenerAction) -> NetworkResult<Self> {
+        let mut ordered: Vec<&ListenerRule> = rules.iter().collect();
+        ordered.sort_by_key(|rule| rule.priority);
+        let rules = ordered
+            .into_iter()
+            .map(|rule| {
+                Ok(CompiledRule {
+                    id: rule.id.clone(),
+                    conditions: rule.conditions.iter().map(CompiledCondition::compile).collect::<NetworkResult<_>>()?,
+                    action: rule.action.clone(),
+                    mirror: rule.mirror.clone().map(|m| Arc::new(MirrorChannel::new(&rule.id, m))),
+                })
+            })
+            .collect::<NetworkResult<_>>()?;
+        Ok(Self {
+            rules,
+            default_action: default_action.clone(),
+        })
+    }
+
+    // Returns the matching rule id (None for the default action) and the action to take
+    pub fn route(&self, request: &RequestInfo) -> (Option<&str>, &ListenerAction) {
+        let (rule, action, _) = self.route_mirrored(request);
+        (rule, action)
+    }
+
+    // As `route`, plus the matching rule's shadow destination if it has one
+    pub fn route_mirrored(&self, request: &RequestInfo) -> (Option<&str>, &ListenerAction, Option<&Arc<MirrorChannel>>) {
+        self.rules
+            .iter()
+            .find(|rule| rule.conditions.iter().all(|c| c.matches(request)))
+            .map(|rule| (Some(rule.id.as_str()), &rule.action, rule.mirror.as_ref()))
+            .unwrap_or((None, &self.default_action, None))
+    }
+
+    pub fn mirrors(&self) -> impl Iterator<Item = &Arc<MirrorChannel>> {
+        self.rules.iter().filter_map(|rule| rule.mirror.as_ref())
+    }
+
+    // Called on a freshly compiled table before it replaces `previous`, so mirror metrics
+    // survive unrelated rule edits
+    pub fn inherit_mirrors(&mut self, previous: &RuleTable) {
+        for rule in &mut self.rules {
+            let Some(old) = previous.rules.iter().find(|r| r.id == rule.id).and_then(|r| r.mirror.as_ref()) else {
+                continue;
+            };
+            if let Some(channel) = rule.mirror.as_mut().and_then(Arc::get_mut) {
+                channel.inherit(old);
+            }
+        }
+    }
+}
+
+fn validate_action(action: &ListenerAction, target_groups: &HashSet<String>, owner: &str) -> NetworkResult<()> {
+    match action {
+        ListenerAction::Forward { target_group } if !target_groups.contains(target_group) => {
+            Err(NetworkError::Validation(format!(
+                "{} forwards to unknown target group {}",
+                owner, target_group
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn validate_mirror(rule: &ListenerRule, target_groups: &HashSet<String>) -> NetworkResult<()> {
+    let Some(mirror) = &rule.mirror else {
+        return Ok(());
+    };
+    let ListenerAction::Forward { target_group } = &rule.action else {
+        return Err(NetworkError::Validation(format!("Rule {} mirrors traffic but does not forward", rule.id)));
+    };
+    if !target_groups.contains(&mirror.target_group) {
+        return Err(NetworkError::Validation(format!(
+            "Rule {} mirrors to unknown target group {}",
+            rule.id, mirror.target_group
+        )));
+    }
+    // Shadowing into the group that serves the request would double its live traffic
+    if mirror.target_group == *target_group {
+        return Err(NetworkError::Validation(format!(
+            "Rule {} mirrors to {}, the target group it forwards to",
+            rule.id, target_group
+        )));
+    }
+    if !(mirror.percentage > 0.0 && mirror.percentage <= 100.0) {
+        return Err(NetworkError::Validation(format!(
+            "Rule {} mirror percentage must be in (0, 100], got {}",
+            rule.id, mirror.percentage
+        )));
+    }
+    if mirror.max_concurrent == 0 {
+        return Err(NetworkError::Validation(format!("Rule {} mirror max_concurrent must be positive", rule.id)));
+    }
+    Ok(())
+}
+
+// Validates the complete rule set of a listener as it would look after a change
+pub fn validate_rules(
+    rules: &[ListenerRule],
+    default_action: &ListenerAction,
+    target_groups: &HashSet<String>,
+) -> NetworkResult<()> {
+    validate_action(default_action, target_groups, "Default action")?;
+
+    let mut ids = HashSet::new();
+    let mut priorities = HashMap::new();
+    for rule in rules {
+        if !ids.insert(rule.id.as_str()) {
+            return Err(NetworkError::Validation(format!("Duplicate rule id {}", rule.id)));
+        }
+        if !(MIN_RULE_PRIORITY..=MAX_RULE_PRIORITY).contains(&rule.priority) {
+            return Err(NetworkError::Validation(format!(
+                "Rule {} priority {} is outside {}..={}",
+                rule.id, rule.priority, MIN_RULE_PRIORITY, MAX_RULE_PRIORITY
+            )));
+        }
+        if let Some(other) = priorities.insert(rule.priority, rule.id.as_str()) {
+            return Err(NetworkError::Validation(format!(
+                "Rules {} and {} share priority {}",
+                other, rule.id, rule.priority
+            )));
+        }
+        if rule.conditions.is_empty() {
+            return Err(NetworkError::Validation(format!("Rule {} has no conditions", rule.id)));
+        }
+        validate_action(&rule.action, target_groups, &format!("Rule {}", rule.id))?;
+        validate_mirror(rule, target_groups)?;
+        for condition in &rule.conditions {
+            CompiledCondition::compile(condition)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn forward(target_group: &str) -> ListenerAction {
+        ListenerAction::Forward {
+            target_group: target_group.to_string(),
+        }
+    }
+
+    fn rule(id: &str, priority: i32, conditions: Vec<RuleCondition>, target_group: &str) -> ListenerRule {
+        ListenerRule {
+            id: id.to_string(),
+            priority,
+            conditions,
+            action: forward(target_group),
+            mirror: None,
+        }
+    }
+
+    #[test]
+    fn test_wildcard_matches() {
+        assert!(wildcard_matches("/api/*", "/api/users/1"));
+        assert!(wildcard_matches("*.example.com", "shop.example.com"));
+        assert!(wildcard_matches("/v?/items", "/v2/items"));
+        assert!(!wildcard_matches("/api/*", "/static/app.js"));
+        assert!(!wildcard_matches("/v?/items", "/v10/items"));
+    }
+
+    #[test]
+    fn test_cidr_contains() {
+        let block = Cidr::parse("10.1.0.0/16").unwrap();
+        assert!(block.contains("10.1.200.3".parse().unwrap()));
+        assert!(!block.contains("10.2.0.1".parse().unwrap()));
+        assert!(Cidr::parse("0.0.0.0/0").unwrap().contains("8.8.8.8".parse().unwrap()));
+        assert!(Cidr::parse("2001:db8::/32").unwrap().contains("2001:db8::1".parse().unwrap()));
+        assert!(Cidr::parse("10.0.0.0/33").is_err());
+    }
+
+    #[test]
+    fn test_route_by_priority() {
+        let rules = vec![
+            rule("admin", 20, vec![RuleCondition::PathPattern("/api/admin/*".into())], "tg-admin"),
+            rule("api", 10, vec![RuleCondition::PathPattern("/api/*".into())], "tg-api"),
+            rule(
+                "internal",
+                5,
+                vec![
+                    RuleCondition::HostHeader("*.internal".into()),
+                    RuleCondition::SourceIp(vec!["10.0.0.0/8".into()]),
+                ],
+                "tg-internal",
+            ),
+        ];
+        let table = RuleTable::compile(&rules, &forward("tg-default")).unwrap();
+
+        assert_eq!(table.route(&RequestInfo::new("shop.example.com", "/api/admin/users")).0, Some("api"));
+        assert_eq!(table.route(&RequestInfo::new("shop.example.com", "/index.html")).0, None);
+        let internal = RequestInfo::new("Billing.Internal", "/").with_source_ip("10.3.4.5".parse().unwrap());
+        assert_eq!(table.route(&internal).0, Some("internal"));
+        let external = RequestInfo::new("billing.internal", "/").with_source_ip("192.168.0.1".parse().unwrap());
+        assert_eq!(table.route(&external).0, None);
+    }
+
+    #[test]
+    fn test_validate_rules() {
+        let groups: HashSet<String> = ["tg-api".to_string()].into();
+        let default = forward("tg-api");
+        let path = || vec![RuleCondition::PathPattern("/*".into())];
+
+        assert!(validate_rules(&[rule("a", 1, path(), "tg-api"), rule("b", 2, path(), "tg-api")], &default, &groups).is_ok());
+        assert!(validate_rules(&[rule("a", 1, path(), "tg-api"), rule("b", 1, path(), "tg-api")], &default, &groups).is_err());
+        assert!(validate_rules(&[rule("a", 1, path(), "tg-missing")], &default, &groups).is_err());
+        assert!(validate_rules(&[rule("a", 0, path(), "tg-api")], &default, &groups).is_err());
+        assert!(validate_rules(&[], &forward("tg-missing"), &groups).is_err());
+        assert!(validate_rules(
+            &[rule("a", 1, vec![RuleCondition::SourceIp(vec!["not-an-ip".into()])], "tg-api")],
+            &default,
+            &groups
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_validate_mirror() {
+        let groups: HashSet<String> = ["tg-api".to_string(), "tg-shadow".to_string()].into();
+        let default = forward("tg-api");
+        let mirrored = |target_group: &str, percentage: f64| ListenerRule {
+            mirror: Some(super::super::TrafficMirror {
+                target_group: target_group.to_string(),
+                percentage,
+                max_concurrent: 10,
+                strip_headers: vec![],
+                tag_header: None,
+            }),
+            ..rule("a", 1, vec![RuleCondition::PathPattern("/*".into())], "tg-api")
+        };
+
+        assert!(validate_rules(&[mirrored("tg-shadow", 5.0)], &default, &groups).is_ok());
+        assert!(validate_rules(&[mirrored("tg-api", 5.0)], &default, &groups).is_err());
+        assert!(validate_rules(&[mirrored("tg-missing", 5.0)], &default, &groups).is_err());
+        assert!(validate_rules(&[mirrored("tg-shadow", 0.0)], &default, &groups).is_err());
+        let redirect = ListenerRule {
+            action: ListenerAction::Redirect {
+                url: "https://example.com".into(),
+                status_code: 301,
+            },
+            ..mirrored("tg-shadow", 5.0)
+        };
+        assert!(validate_rules(&[redirect], &default, &groups).is_err());
+    }
+}
@@ -0,0 +1,1208 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use rustls::ServerConfig;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tokio_rustls::TlsAcceptor;
+use tracing::{debug, info, warn};
+
+use crate::error::{NetworkError, NetworkResult};
+use sirsi_key_vault::secret::SecretManager;
+use super::listener_tls::ListenerCertificates;
+use super::metrics::{render_prometheus, LoadBalancerTelemetry, PrometheusSeries, TrafficCounters, DEFAULT_RETENTION_MINUTES};
+use super::mirror::{exchange, read_request, HttpRequest, HttpResponse, MirrorChannel, MirrorMetrics};
+use super::rules::{validate_rules, RequestInfo, RuleTable};
+use super::upstream::{target_address, TargetTlsReport, UpstreamConnector, UpstreamStream};
+use super::{
+    ListenerAction, ListenerRule, ListenerRuleManager, LoadBalancer, LoadBalancerManager, LoadBalancerMetrics,
+    Target, TargetGroup, TargetGroupManager, TargetHealth,
+};
+
+// Matches the ELB default idle timeout
+const UPSTREAM_TIMEOUT: Duration = Duration::from_secs(60);
+// Shorter for shadows, which hold a mirror permit for as long as they are outstanding
+const MIRROR_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Default)]
+struct State {
+    load_balancers: HashMap<String, LoadBalancer>,
+    target_groups: HashMap<String, TargetGroup>,
+    targets: HashMap<String, Vec<Target>>,
+}
+
+impl State {
+    fn find_listener(&mut self, listener_id: &str) -> NetworkResult<&mut super::Listener> {
+        self.load_balancers
+            .values_mut()
+            .flat_map(|lb| lb.listeners.iter_mut())
+            .find(|listener| listener.id == listener_id)
+            .ok_or_else(|| NetworkError::NotFound(format!("Listener {} not found", listener_id)))
+    }
+
+    fn target_group_ids(&self) -> HashSet<String> {
+        self.target_groups.keys().cloned().collect()
+    }
+
+    fn references_target_group(&self, group_id: &str) -> bool {
+        self.load_balancers.values().flat_map(|lb| &lb.listeners).any(|listener| {
+            std::iter::once(&listener.default_action)
+                .chain(listener.rules.iter().map(|rule| &rule.action))
+                .any(|action| matches!(action, ListenerAction::Forward { target_group } if target_group == group_id))
+                || listener
+                    .rules
+                    .iter()
+                    .filter_map(|rule| rule.mirror.as_ref())
+                    .any(|mirror| mirror.target_group == group_id)
+        })
+    }
+
+    // Healthy and unhealthy targets across every target group the load balancer forwards to
+    fn host_counts(&self, lb: &LoadBalancer) -> (i32, i32) {
+        let groups: HashSet<&str> = lb
+            .listeners
+            .iter()
+            .flat_map(|listener| std::iter::once(&listener.default_action).chain(listener.rules.iter().map(|r| &r.action)))
+            .filter_map(|action| match action {
+                ListenerAction::Forward { target_group } => Some(target_group.as_str()),
+                _ => None,
+            })
+            .collect();
+        let targets = groups.into_iter().filter_map(|g| self.targets.get(g)).flatten();
+        targets.fold((0, 0), |(healthy, unhealthy), target| match target.status {
+            TargetHealth::Healthy => (healthy + 1, unhealthy),
+            TargetHealth::Unhealthy => (healthy, unhealthy + 1),
+            _ => (healthy, unhealthy),
+        })
+    }
+}
+
+// Self-hosted load balancer. Control-plane changes serialize on `state`; the data path only
+// touches `routes`, whose per-listener tables are replaced wholesale by shadow-copy swaps.
+#[derive(Default)]
+pub struct SoftwareLoadBalancer {
+    state: Mutex<State>,
+    routes: RwLock<HashMap<String, Arc<RuleTable>>>,
+    telemetry: RwLock<HashMap<String, Arc<LoadBalancerTelemetry>>>,
+    upstream: UpstreamConnector,
+    next_target: AtomicUsize,
+    certificates: Option<Arc<ListenerCertificates>>,
+    tls: RwLock<HashMap<String, Arc<ServerConfig>>>,
+}
+
+impl SoftwareLoadBalancer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Needed for target groups whose backend TLS presents a key-vault client certificate
+    pub fn with_secrets(secrets: Arc<dyn SecretManager>) -> Self {
+        Self::with_connector(UpstreamConnector::new(Some(secrets)))
+    }
+
+    pub fn with_connector(upstream: UpstreamConnector) -> Self {
+        Self {
+            upstream,
+            ..Self::default()
+        }
+    }
+
+    // Needed for HTTPS and TLS listeners whose certificates live in the key vault
+    pub fn with_listener_certificates(mut self, certificates: Arc<ListenerCertificates>) -> Self {
+        self.certificates = Some(certificates);
+        self
+    }
+
+    // Attaches every listener that serves key-vault certificates. On failure nothing newly
+    // attached is left behind.
+    async fn attach_certificates(&self, lb: &LoadBalancer, previous: &[String]) -> NetworkResult<Vec<(String, Arc<ServerConfig>)>> {
+        let mut configs = Vec::new();
+        for listener in lb.listeners.iter().filter(|l| ListenerCertificates::manages(l)) {
+            let result = match &self.certificates {
+                Some(certificates) => certificates.attach_listener(listener).await,
+                None => Err(NetworkError::Config(format!(
+                    "Listener {} uses key-vault certificates but no listener certificate manager is configured",
+                    listener.id
+                ))),
+            };
+            match result {
+                Ok(config) => configs.push((listener.id.clone(), config)),
+                Err(e) => {
+                    if let Some(certificates) = &self.certificates {
+                        for (listener_id, _) in configs.iter().filter(|(id, _)| !previous.contains(id)) {
+                            certificates.detach_listener(listener_id);
+                        }
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        Ok(configs)
+    }
+
+    fn detach_certificates(&self, listener_ids: &[String]) {
+        let mut tls = self.tls.write().expect("listener tls lock poisoned");
+        for listener_id in listener_ids {
+            if tls.remove(listener_id).is_some() {
+                if let Some(certificates) = &self.certificates {
+                    certificates.detach_listener(listener_id);
+                }
+            }
+        }
+    }
+
+    async fn group_and_target(&self, group_id: &str, target_id: &str) -> NetworkResult<(TargetGroup, Target)> {
+        let state = self.state.lock().await;
+        let group = state
+            .target_groups
+            .get(group_id)
+            .cloned()
+            .ok_or_else(|| NetworkError::NotFound(format!("Target group {} not found", group_id)))?;
+        let target = state
+            .targets
+            .get(group_id)
+            .and_then(|targets| targets.iter().find(|t| t.id == target_id))
+            .cloned()
+            .ok_or_else(|| NetworkError::NotFound(format!("Target {} not found in {}", target_id, group_id)))?;
+        Ok((group, target))
+    }
+
+    // Feeds a TLS handshake outcome into the target's health
+    async fn observe_handshake(&self, group: &TargetGroup, target: &Target, success: bool) {
+        if let Some(health) = self.upstream.record_handshake(group, target, success) {
+            let mut state = self.state.lock().await;
+            if let Some(t) = state
+                .targets
+                .get_mut(&group.id)
+                .and_then(|targets| targets.iter_mut().find(|t| t.id == target.id))
+            {
+                info!("Target {} in {} is now {:?} after TLS handshakes", target.id, group.id, health);
+                t.status = health;
+            }
+        }
+    }
+
+    pub async fn connect_upstream(&self, group_id: &str, target_id: &str) -> NetworkResult<UpstreamStream> {
+        let (group, target) = self.group_and_target(group_id, target_id).await?;
+        let result = self.upstream.connect(&group, &target).await;
+        if group.backend_tls.is_some() {
+            match &result {
+                Ok(_) => self.observe_handshake(&group, &target, true).await,
+                // TCP-level failures belong to the regular health checks, not TLS tracking
+                Err(NetworkError::Unavailable(_)) => {}
+                Err(_) => self.observe_handshake(&group, &target, false).await,
+            }
+        }
+        result
+    }
+
+    // Diagnostic: handshakes with every registered target of the group and reports each outcome
+    pub async fn validate_backend_tls(&self, group_id: &str) -> NetworkResult<Vec<TargetTlsReport>> {
+        let group = self.get_target_group(group_id).await?;
+        if group.backend_tls.is_none() {
+            return Err(NetworkError::Validation(format!("Target group {} has no backend TLS settings", group_id)));
+        }
+        let targets = self.describe_target_health(group_id).await?;
+        let mut reports = Vec::with_capacity(targets.len());
+        for target in targets {
+            let address = target_address(&group, &target)
+                .map(|a| a.to_string())
+                .unwrap_or_else(|_| target.id.clone());
+            let result = self.upstream.probe(&group, &target).await;
+            if !matches!(result, Err(NetworkError::Unavailable(_))) {
+                self.observe_handshake(&group, &target, result.is_ok()).await;
+            }
+            reports.push(TargetTlsReport {
+                target_id: target.id.clone(),
+                address,
+                success: result.is_ok(),
+                error: result.err().map(|e| e.to_string()),
+            });
+        }
+        Ok(reports)
+    }
+
+    pub async fn reload_backend_certificates(&self) -> NetworkResult<Vec<String>> {
+        let groups: Vec<TargetGroup> = self.state.lock().await.target_groups.values().cloned().collect();
+        self.upstream.reload_rotated(&groups).await
+    }
+
+    pub fn spawn_certificate_reloader(self: Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = self.reload_backend_certificates().await {
+                    warn!("Backend certificate reload failed: {}", e);
+                }
+            }
+        })
+    }
+
+    // Hot path: cloning the Arc is the only work done under the lock
+    fn table(&self, listener_id: &str) -> NetworkResult<Arc<RuleTable>> {
+        self.routes
+            .read()
+            .expect("route table lock poisoned")
+            .get(listener_id)
+            .cloned()
+            .ok_or_else(|| NetworkError::NotFound(format!("Listener {} not found", listener_id)))
+    }
+
+    pub fn route(&self, listener_id: &str, request: &RequestInfo) -> NetworkResult<(Option<String>, ListenerAction)> {
+        let table = self.table(listener_id)?;
+        let (rule, action) = table.route(request);
+        Ok((rule.map(str::to_string), action.clone()))
+    }
+
+    // Round-robin across targets that are not known to be unhealthy
+    async fn pick_target(&self, group_id: &str) -> Option<String> {
+        let state = self.state.lock().await;
+        let candidates: Vec<&Target> = state
+            .targets
+            .get(group_id)?
+            .iter()
+            .filter(|t| matches!(t.status, TargetHealth::Healthy | TargetHealth::Initial))
+            .collect();
+        if candidates.is_empty() {
+            return None;
+        }
+        let next = self.next_target.fetch_add(1, Ordering::Relaxed) % candidates.len();
+        Some(candidates[next].id.clone())
+    }
+
+    async fn send_to_group(&self, group_id: &str, request: &HttpRequest) -> NetworkResult<HttpResponse> {
+        let target = self
+            .pick_target(group_id)
+            .await
+            .ok_or_else(|| NetworkError::Unavailable(format!("No healthy targets in {}", group_id)))?;
+        let mut stream = self.connect_upstream(group_id, &target).await?;
+        exchange(&mut stream, request).await
+    }
+
+    // Fire and forget: the primary path never waits on the shadow or sees its outcome
+    fn spawn_shadow(self: &Arc<Self>, channel: Arc<MirrorChannel>, request: &HttpRequest) {
+        let Some(permit) = channel.admit() else {
+            return;
+        };
+        let shadow = channel.shadow_request(request);
+        let lb = self.clone();
+        tokio::spawn(async move {
+            let _permit = permit;
+            let group = &channel.config().target_group;
+            let success = match tokio::time::timeout(MIRROR_TIMEOUT, lb.send_to_group(group, &shadow)).await {
+                Ok(Ok(response)) => response.status < 500,
+                Ok(Err(e)) => {
+                    debug!("Mirrored request to {} failed: {}", group, e);
+                    false
+                }
+                Err(_) => {
+                    debug!("Mirrored request to {} timed out", group);
+                    false
+                }
+            };
+            channel.record(success);
+        });
+    }
+
+    // Data path for HTTP listeners: applies the matching rule and returns the client-facing response
+    pub async fn forward_http(self: &Arc<Self>, listener_id: &str, request: HttpRequest) -> NetworkResult<HttpResponse> {
+        let table = self.table(listener_id)?;
+        let (_, action, mirror) = table.route_mirrored(&request.request_info());
+        let response = match action {
+            ListenerAction::Forward { target_group } => {
+                if let Some(channel) = mirror {
+                    self.spawn_shadow(channel.clone(), &request);
+                }
+                match tokio::time::timeout(UPSTREAM_TIMEOUT, self.send_to_group(target_group, &request)).await {
+                    Ok(Ok(response)) => response,
+                    Ok(Err(NetworkError::Unavailable(e))) => {
+                        warn!("Listener {} could not reach {}: {}", listener_id, target_group, e);
+                        HttpResponse::new(503, "text/plain", "Service Unavailable")
+                    }
+                    Ok(Err(e)) => {
+                        warn!("Listener {} got a bad response from {}: {}", listener_id, target_group, e);
+                        HttpResponse::new(502, "text/plain", "Bad Gateway")
+                    }
+                    Err(_) => HttpResponse::new(504, "text/plain", "Gateway Timeout"),
+                }
+            }
+            ListenerAction::Redirect { url, status_code } => HttpResponse {
+                status: *status_code,
+                headers: vec![("Location".to_string(), url.clone())],
+                body: Vec::new(),
+            },
+            ListenerAction::FixedResponse {
+                content_type,
+                message,
+                status_code,
+            } => HttpResponse::new(*status_code, content_type, message.as_bytes()),
+        };
+
+        let lb_id = self
+            .state
+            .lock()
+            .await
+            .load_balancers
+            .values()
+            .find(|lb| lb.listeners.iter().any(|l| l.id == listener_id))
+            .map(|lb| lb.id.clone());
+        if let Some(counters) = lb_id.and_then(|id| self.traffic_counters(&id).ok()) {
+            counters.record_bytes((request.body.len() + response.body.len()) as u64);
+            counters.record_request(response.status);
+        }
+        Ok(response)
+    }
+
+    pub fn mirror_metrics(&self, listener_id: &str) -> NetworkResult<Vec<MirrorMetrics>> {
+        Ok(self.table(listener_id)?.mirrors().map(|channel| channel.metrics()).collect())
+    }
+
+    // Handed to the data path once per load balancer; recording through it never takes a lock
+    pub fn traffic_counters(&self, lb_id: &str) -> NetworkResult<Arc<TrafficCounters>> {
+        self.telemetry(lb_id).map(|t| t.counters.clone())
+    }
+
+    fn telemetry(&self, lb_id: &str) -> NetworkResult<Arc<LoadBalancerTelemetry>> {
+        self.telemetry
+            .read()
+            .expect("telemetry lock poisoned")
+            .get(lb_id)
+            .cloned()
+            .ok_or_else(|| NetworkError::NotFound(format!("Load balancer {} not found", lb_id)))
+    }
+
+    // Closes the current minute for every load balancer
+    pub async fn sample_metrics(&self) -> Vec<LoadBalancerMetrics> {
+        let state = self.state.lock().await;
+        let now = Utc::now();
+        let mut samples: Vec<LoadBalancerMetrics> = state
+            .load_balancers
+            .values()
+            .filter_map(|lb| {
+                let telemetry = self.telemetry(&lb.id).ok()?;
+                let (healthy, unhealthy) = state.host_counts(lb);
+                let mut history = telemetry.history.lock().expect("metrics history lock poisoned");
+                Some(history.sample(&lb.id, now, telemetry.counters.snapshot(), healthy, unhealthy))
+            })
+            .collect();
+        samples.sort_by(|a, b| a.id.cmp(&b.id));
+        samples
+    }
+
+    pub fn spawn_metrics_sampler(self: Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            // The first tick fires immediately and would record an empty partial minute
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                self.sample_metrics().await;
+            }
+        })
+    }
+
+    pub async fn prometheus_metrics(&self) -> String {
+        let state = self.state.lock().await;
+        let mut ids: Vec<&String> = state.load_balancers.keys().collect();
+        ids.sort();
+        let series: Vec<(String, _, (i32, i32))> = ids
+            .into_iter()
+            .filter_map(|id| {
+                let telemetry = self.telemetry(id).ok()?;
+                Some((id.clone(), telemetry.counters.snapshot(), state.host_counts(&state.load_balancers[id])))
+            })
+            .collect();
+        drop(state);
+        let series: Vec<PrometheusSeries<'_>> = series
+            .iter()
+            .map(|(id, counters, (healthy, unhealthy))| PrometheusSeries {
+                id,
+                counters: *counters,
+                healthy_hosts: *healthy,
+                unhealthy_hosts: *unhealthy,
+            })
+            .collect();
+        render_prometheus(&series)
+    }
+
+    // Minimal admin endpoint: answers `GET /metrics` and 404s everything else
+    pub async fn serve_prometheus(self: Arc<Self>, listener: TcpListener) -> NetworkResult<()> {
+        loop {
+            let (mut stream, peer) = listener
+                .accept()
+                .await
+                .map_err(|e| NetworkError::LoadBalancer(format!("Admin listener failed: {}", e)))?;
+            let lb = self.clone();
+            tokio::spawn(async move {
+                let mut buf = [0u8; 1024];
+                let n = match stream.read(&mut buf).await {
+                    Ok(n) => n,
+                    Err(e) => {
+                        warn!("Failed to read admin request from {}: {}", peer, e);
+                        return;
+                    }
+                };
+                let request = String::from_utf8_lossy(&buf[..n]);
+                let response = if request.starts_with("GET /metrics ") {
+                    let body = lb.prometheus_metrics().await;
+                    format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    )
+                } else {
+                    "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+                };
+                if let Err(e) = stream.write_all(response.as_bytes()).await {
+                    warn!("Failed to write admin response to {}: {}", peer, e);
+                }
+            });
+        }
+    }
+
+    // Data path for HTTPS listeners. The server config is looked up per connection, so a
+    // certificate reload only affects handshakes that start after it.
+    pub async fn serve_tls(self: Arc<Self>, listener_id: String, listener: TcpListener) -> NetworkResult<()> {
+        loop {
+            let (tcp, peer) = listener
+                .accept()
+                .await
+                .map_err(|e| NetworkError::LoadBalancer(format!("Listener {} failed: {}", listener_id, e)))?;
+            let Some(config) = self.tls.read().expect("listener tls lock poisoned").get(&listener_id).cloned() else {
+                warn!("Listener {} has no TLS certificates; dropping connection from {}", listener_id, peer);
+                continue;
+            };
+            let lb = self.clone();
+            let listener_id = listener_id.clone();
+            tokio::spawn(async move {
+                let mut stream = match TlsAcceptor::from(config).accept(tcp).await {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        debug!("TLS handshake with {} on listener {} failed: {}", peer, listener_id, e);
+                        return;
+                    }
+                };
+                let mut buf = Vec::new();
+                loop {
+                    let request = match read_request(&mut stream, &mut buf).await {
+                        Ok(Some(request)) => request,
+                        Ok(None) => return,
+                        Err(e) => {
+                            debug!("Dropping connection from {} on listener {}: {}", peer, listener_id, e);
+                            return;
+                        }
+                    };
+                    let close = request.header("connection").is_some_and(|v| v.eq_ignore_ascii_case("close"));
+                    let response = match lb.forward_http(&listener_id, request).await {
+                        Ok(response) => response,
+                        Err(e) => {
+                            warn!("Listener {} could not route a request from {}: {}", listener_id, peer, e);
+                            HttpResponse::new(503, "text/plain", "Service Unavailable")
+                        }
+                    };
+                    if stream.write_all(&response.encode()).await.is_err() || close {
+                        return;
+                    }
+                }
+            });
+        }
+    }
+
+    fn swap_table(&self, listener_id: &str, mut table: RuleTable) {
+        let mut routes = self.routes.write().expect("route table lock poisoned");
+        if let Some(previous) = routes.get(listener_id) {
+            table.inherit_mirrors(previous);
+        }
+        routes.insert(listener_id.to_string(), Arc::new(table));
+    }
+
+    fn validate_load_balancer(state: &State, lb: &LoadBalancer) -> NetworkResult<Vec<(String, RuleTable)>> {
+        let groups = state.target_group_ids();
+        lb.listeners
+            .iter()
+            .map(|listener| {
+                validate_rules(&listener.rules, &listener.default_action, &groups)?;
+                Ok((listener.id.clone(), RuleTable::compile(&listener.rules, &listener.default_action)?))
+            })
+            .collect()
+    }
+
+    // Validates the listener's prospective rule set, then publishes it to both the record and the data path
+    async fn update_rules<F>(&self, listener_id: &str, change: F) -> NetworkResult<Vec<ListenerRule>>
+    where
+        F: FnOnce(&mut Vec<ListenerRule>) -> NetworkResult<()> + Send,
+    {
+        let mut state = self.state.lock().await;
+        let groups = state.target_group_ids();
+        let listener = state.find_listener(listener_id)?;
+
+        let mut shadow = listener.rules.clone();
+        change(&mut shadow)?;
+        validate_rules(&shadow, &listener.default_action, &groups)?;
+        let table = RuleTable::compile(&shadow, &listener.default_action)?;
+
+        shadow.sort_by_key(|rule| rule.priority);
+        listener.rules = shadow.clone();
+        self.swap_table(listener_id, table);
+        Ok(shadow)
+    }
+}
+
+#[async_trait]
+impl ListenerRuleManager for SoftwareLoadBalancer {
+    async fn add_rule(&self, listener_id: &str, rule: ListenerRule) -> NetworkResult<ListenerRule> {
+        let added = rule.clone();
+        self.update_rules(listener_id, move |rules| {
+            if rules.iter().any(|r| r.id == rule.id) {
+                return Err(NetworkError::Conflict(format!("Rule {} already exists", rule.id)));
+            }
+            rules.push(rule);
+            Ok(())
+        })
+        .await?;
+        info!("Added rule {} to listener {}", added.id, listener_id);
+        Ok(added)
+    }
+
+    async fn remove_rule(&self, listener_id: &str, rule_id: &str) -> NetworkResult<()> {
+        self.update_rules(listener_id, |rules| {
+            let before = rules.len();
+            rules.retain(|r| r.id != rule_id);
+            if rules.len() == before {
+                return Err(NetworkError::NotFound(format!("Rule {} not found", rule_id)));
+            }
+            Ok(())
+        })
+        .await?;
+        info!("Removed rule {} from listener {}", rule_id, listener_id);
+        Ok(())
+    }
+
+    async fn list_rules(&self, listener_id: &str) -> NetworkResult<Vec<ListenerRule>> {
+        let mut state = self.state.lock().await;
+        Ok(state.find_listener(listener_id)?.rules.clone())
+    }
+
+    // The listener's existing priority values are reassigned in the requested order
+    async fn set_rule_priorities(&self, listener_id: &str, ordered_rule_ids: Vec<String>) -> NetworkResult<Vec<ListenerRule>> {
+        let rules = self
+            .update_rules(listener_id, |rules| {
+                let current: HashSet<&str> = rules.iter().map(|r| r.id.as_str()).collect();
+                let requested: HashSet<&str> = ordered_rule_ids.iter().map(String::as_str).collect();
+                if requested.len() != ordered_rule_ids.len() || current != requested {
+                    return Err(NetworkError::Validation(format!(
+                        "Priority order for listener {} must list each of its {} rules exactly once",
+                        listener_id,
+                        rules.len()
+                    )));
+                }
+
+                let mut priorities: Vec<i32> = rules.iter().map(|r| r.priority).collect();
+                priorities.sort_unstable();
+                let position: HashMap<&str, usize> =
+                    ordered_rule_ids.iter().enumerate().map(|(i, id)| (id.as_str(), i)).collect();
+                for rule in rules.iter_mut() {
+                    rule.priority = priorities[position[rule.id.as_str()]];
+                }
+                Ok(())
+            })
+            .await?;
+        info!("Reordered {} rules on listener {}", rules.len(), listener_id);
+        Ok(rules)
+    }
+}
+
+#[async_trait]
+impl LoadBalancerManager for SoftwareLoadBalancer {
+    async fn create_load_balancer(&self, mut lb: LoadBalancer) -> NetworkResult<LoadBalancer> {
+        let mut state = self.state.lock().await;
+        if state.load_balancers.contains_key(&lb.id) {
+            return Err(NetworkError::Conflict(format!("Load balancer {} already exists", lb.id)));
+        }
+        let duplicate = {
+            let routes = self.routes.read().expect("route table lock poisoned");
+            lb.listeners.iter().find(|l| routes.contains_key(&l.id)).map(|l| l.id.clone())
+        };
+        if let Some(listener_id) = duplicate {
+            return Err(NetworkError::Conflict(format!("Listener {} already exists", listener_id)));
+        }
+        let tables = Self::validate_load_balancer(&state, &lb)?;
+        let configs = self.attach_certificates(&lb, &[]).await?;
+        let now = Utc::now();
+        lb.created_at = now;
+        lb.updated_at = now;
+        for (listener_id, table) in tables {
+            self.swap_table(&listener_id, table);
+        }
+        self.tls.write().expect("listener tls lock poisoned").extend(configs);
+        self.telemetry
+            .write()
+            .expect("telemetry lock poisoned")
+            .insert(lb.id.clone(), Arc::new(LoadBalancerTelemetry::new(DEFAULT_RETENTION_MINUTES)));
+        state.load_balancers.insert(lb.id.clone(), lb.clone());
+        Ok(lb)
+    }
+
+    async fn modify_load_balancer(&self, mut lb: LoadBalancer) -> NetworkResult<LoadBalancer> {
+        let mut state = self.state.lock().await;
+        let existing = state
+            .load_balancers
+            .get(&lb.id)
+            .ok_or_else(|| NetworkError::NotFound(format!("Load balancer {} not found", lb.id)))?;
+        let removed: Vec<String> = existing
+            .listeners
+            .iter()
+            .filter(|old| !lb.listeners.iter().any(|new| new.id == old.id))
+            .map(|old| old.id.clone())
+            .collect();
+        let previous: Vec<String> = existing.listeners.iter().map(|l| l.id.clone()).collect();
+        lb.created_at = existing.created_at;
+        lb.updated_at = Utc::now();
+
+        let tables = Self::validate_load_balancer(&state, &lb)?;
+        let configs = self.attach_certificates(&lb, &previous).await?;
+        for (listener_id, table) in tables {
+            self.swap_table(&listener_id, table);
+        }
+        // Listeners that were removed or no longer serve key-vault certificates
+        let untracked: Vec<String> = previous
+            .iter()
+            .filter(|id| !configs.iter().any(|(attached, _)| attached == *id))
+            .cloned()
+            .collect();
+        self.detach_certificates(&untracked);
+        self.tls.write().expect("listener tls lock poisoned").extend(configs);
+        let mut routes = self.routes.write().expect("route table lock poisoned");
+        for listener_id in removed {
+            routes.remove(&listener_id);
+        }
+        drop(routes);
+        state.load_balancers.insert(lb.id.clone(), lb.clone());
+        Ok(lb)
+    }
+
+    async fn delete_load_balancer(&self, id: &str) -> NetworkResult<()> {
+        let mut state = self.state.lock().await;
+        let lb = state
+            .load_balancers
+            .remove(id)
+            .ok_or_else(|| NetworkError::NotFound(format!("Load balancer {} not found", id)))?;
+        let mut routes = self.routes.write().expect("route table lock poisoned");
+        for listener in &lb.listeners {
+            routes.remove(&listener.id);
+        }
+        drop(routes);
+        self.detach_certificates(&lb.listeners.iter().map(|l| l.id.clone()).collect::<Vec<_>>());
+        self.telemetry.write().expect("telemetry lock poisoned").remove(id);
+        Ok(())
+    }
+
+    async fn get_load_balancer(&self, id: &str) -> NetworkResult<LoadBalancer> {
+        self.state
+            .lock()
+            .await
+            .load_balancers
+            .get(id)
+            .cloned()
+            .ok_or_else(|| NetworkError::NotFound(format!("Load balancer {} not found", id)))
+    }
+
+    async fn list_load_balancers(&self) -> NetworkResult<Vec<LoadBalancer>> {
+        let mut lbs: Vec<LoadBalancer> = self.state.lock().await.load_balancers.values().cloned().collect();
+        lbs.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(lbs)
+    }
+
+    async fn get_metrics(&self, id: &str, window: chrono::Duration) -> NetworkResult<Vec<LoadBalancerMetrics>> {
+        let telemetry = self.telemetry(id)?;
+        let history = telemetry.history.lock().expect("metrics history lock poisoned");
+        Ok(history.since(Utc::now() - window))
+    }
+}
+
+#[async_trait]
+impl TargetGroupManager for SoftwareLoadBalancer {
+    async fn create_target_group(&self, group: TargetGroup) -> NetworkResult<TargetGroup> {
+        let mut state = self.state.lock().await;
+        if state.target_groups.contains_key(&group.id) {
+            return Err(NetworkError::Conflict(format!("Target group {} already exists", group.id)));
+        }
+        state.target_groups.insert(group.id.clone(), group.clone());
+        Ok(group)
+    }
+
+    async fn modify_target_group(&self, group: TargetGroup) -> NetworkResult<TargetGroup> {
+        let mut state = self.state.lock().await;
+        let existing = state
+            .target_groups
+            .get_mut(&group.id)
+            .ok_or_else(|| NetworkError::NotFound(format!("Target group {} not found", group.id)))?;
+        *existing = group.clone();
+        drop(state);
+        self.upstream.invalidate(&group.id).await;
+        Ok(group)
+    }
+
+    async fn delete_target_group(&self, id: &str) -> NetworkResult<()> {
+        let mut state = self.state.lock().await;
+        if state.references_target_group(id) {
+            return Err(NetworkError::Conflict(format!("Target group {} is in use by a listener", id)));
+        }
+        state
+            .target_groups
+            .remove(id)
+            .ok_or_else(|| NetworkError::NotFound(format!("Target group {} not found", id)))?;
+        state.targets.remove(id);
+        drop(state);
+        self.upstream.invalidate(id).await;
+        Ok(())
+    }
+
+    async fn get_target_group(&self, id: &str) -> NetworkResult<TargetGroup> {
+        self.state
+            .lock()
+            .await
+            .target_groups
+            .get(id)
+            .cloned()
+            .ok_or_else(|| NetworkError::NotFound(format!("Target group {} not found", id)))
+    }
+
+    async fn list_target_groups(&self) -> NetworkResult<Vec<TargetGroup>> {
+        let mut groups: Vec<TargetGroup> = self.state.lock().await.target_groups.values().cloned().collect();
+        groups.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(groups)
+    }
+
+    async fn register_targets(&self, group_id: &str, targets: Vec<Target>) -> NetworkResult<()> {
+        let mut state = self.state.lock().await;
+        if !state.target_groups.contains_key(group_id) {
+            return Err(NetworkError::NotFound(format!("Target group {} not found", group_id)));
+        }
+        let registered = state.targets.entry(group_id.to_string()).or_default();
+        for target in targets {
+            registered.retain(|t| t.id != target.id);
+            registered.push(target);
+        }
+        Ok(())
+    }
+
+    async fn deregister_targets(&self, group_id: &str, target_ids: Vec<String>) -> NetworkResult<()> {
+        let mut state = self.state.lock().await;
+        if !state.target_groups.contains_key(group_id) {
+            return Err(NetworkError::NotFound(format!("Target group {} not found", group_id)));
+        }
+        if let Some(registered) = state.targets.get_mut(group_id) {
+            registered.retain(|t| !target_ids.contains(&t.id));
+        }
+        Ok(())
+    }
+
+    async fn describe_target_health(&self, group_id: &str) -> NetworkResult<Vec<Target>> {
+        let state = self.state.lock().await;
+        if !state.target_groups.contains_key(group_id) {
+            return Err(NetworkError::NotFound(format!("Target group {} not found", group_id)));
+        }
+        Ok(state.targets.get(group_id).cloned().unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loadbalancer::{
+        HealthCheck, IpAddressType, Listener, ListenerProtocol, LoadBalancerScheme, LoadBalancerStatus,
+        LoadBalancerType, LoadBalancingAlgorithm, RuleCondition, TargetGroupAttributes, TargetType,
+    };
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    fn health_check() -> HealthCheck {
+        HealthCheck {
+            protocol: ListenerProtocol::HTTP,
+            port: None,
+            path: Some("/health".into()),
+            interval_seconds: 10,
+            timeout_seconds: 5,
+            healthy_threshold: 2,
+            unhealthy_threshold: 2,
+        }
+    }
+
+    fn target_group(id: &str) -> TargetGroup {
+        TargetGroup {
+            id: id.into(),
+            name: id.into(),
+            protocol: ListenerProtocol::HTTP,
+            port: 8080,
+            target_type: TargetType::IP,
+            vpc_id: "vpc-1".into(),
+            health_check: health_check(),
+            attributes: TargetGroupAttributes {
+                deregistration_delay_seconds: 30,
+                stickiness_enabled: false,
+                stickiness_type: None,
+                stickiness_duration_seconds: None,
+                load_balancing_algorithm: LoadBalancingAlgorithm::RoundRobin,
+            },
+            backend_tls: None,
+        }
+    }
+
+    fn path_rule(id: &str, priority: i32, path: &str, target_group: &str) -> ListenerRule {
+        ListenerRule {
+            id: id.into(),
+            priority,
+            conditions: vec![RuleCondition::PathPattern(path.into())],
+            action: ListenerAction::Forward {
+                target_group: target_group.into(),
+            },
+            mirror: None,
+        }
+    }
+
+    async fn load_balancer(services: usize) -> SoftwareLoadBalancer {
+        let lb = SoftwareLoadBalancer::new();
+        for i in 0..services {
+            lb.create_target_group(target_group(&format!("tg-{}", i))).await.unwrap();
+        }
+        lb.create_load_balancer(LoadBalancer {
+            id: "lb-1".into(),
+            name: "edge".into(),
+            lb_type: LoadBalancerType::Application,
+            status: LoadBalancerStatus::Active,
+            scheme: LoadBalancerScheme::Internet,
+            ip_address_type: IpAddressType::IPv4,
+            subnets: vec![],
+            security_groups: vec![],
+            listeners: vec![Listener {
+                id: "http".into(),
+                protocol: ListenerProtocol::HTTP,
+                port: 80,
+                ssl_policy: None,
+                certificates: None,
+                default_action: ListenerAction::FixedResponse {
+                    content_type: "text/plain".into(),
+                    message: "not found".into(),
+                    status_code: 404,
+                },
+                rules: vec![],
+            }],
+            health_check: health_check(),
+            tags: HashMap::new(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        })
+        .await
+        .unwrap();
+        lb
+    }
+
+    #[tokio::test]
+    async fn test_rule_crud_validation() {
+        let lb = load_balancer(2).await;
+        lb.add_rule("http", path_rule("a", 10, "/a/*", "tg-0")).await.unwrap();
+
+        let collision = lb.add_rule("http", path_rule("b", 10, "/b/*", "tg-1")).await;
+        assert!(matches!(collision, Err(NetworkError::Validation(_))));
+        let missing = lb.add_rule("http", path_rule("b", 20, "/b/*", "tg-missing")).await;
+        assert!(matches!(missing, Err(NetworkError::Validation(_))));
+        assert!(matches!(
+            lb.add_rule("http", path_rule("a", 30, "/c/*", "tg-1")).await,
+            Err(NetworkError::Conflict(_))
+        ));
+        assert!(matches!(lb.delete_target_group("tg-0").await, Err(NetworkError::Conflict(_))));
+
+        lb.add_rule("http", path_rule("b", 20, "/b/*", "tg-1")).await.unwrap();
+        assert_eq!(lb.list_rules("http").await.unwrap().len(), 2);
+        assert!(lb.set_rule_priorities("http", vec!["a".into()]).await.is_err());
+        assert!(lb.set_rule_priorities("http", vec!["a".into(), "a".into()]).await.is_err());
+
+        lb.remove_rule("http", "a").await.unwrap();
+        let (rule, action) = lb.route("http", &RequestInfo::new("example.com", "/a/x")).unwrap();
+        assert_eq!(rule, None);
+        assert!(matches!(action, ListenerAction::FixedResponse { status_code: 404, .. }));
+    }
+
+    #[tokio::test]
+    async fn test_reorder_changes_precedence() {
+        let lb = load_balancer(2).await;
+        lb.add_rule("http", path_rule("api", 10, "/api/*", "tg-0")).await.unwrap();
+        lb.add_rule("http", path_rule("admin", 20, "/api/admin/*", "tg-1")).await.unwrap();
+        let request = RequestInfo::new("example.com", "/api/admin/users");
+        assert_eq!(lb.route("http", &request).unwrap().0.as_deref(), Some("api"));
+
+        let rules = lb
+            .set_rule_priorities("http", vec!["admin".into(), "api".into()])
+            .await
+            .unwrap();
+        assert_eq!(rules[0].id, "admin");
+        assert_eq!(rules[0].priority, 10);
+        assert_eq!(lb.route("http", &request).unwrap().0.as_deref(), Some("admin"));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_reorder_is_atomic_under_concurrent_matching() {
+        const SERVICES: usize = 6;
+        let lb = Arc::new(load_balancer(SERVICES).await);
+        for i in 0..SERVICES {
+            let rule = path_rule(&format!("svc-{}", i), (i as i32 + 1) * 10, &format!("/svc{}/*", i), &format!("tg-{}", i));
+            lb.add_rule("http", rule).await.unwrap();
+        }
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let checked = Arc::new(AtomicUsize::new(0));
+        let matchers: Vec<_> = (0..4)
+            .map(|worker| {
+                let (lb, stop, checked) = (lb.clone(), stop.clone(), checked.clone());
+                std::thread::spawn(move || {
+                    let mut n = worker;
+                    while !stop.load(Ordering::Relaxed) {
+                        let service = n % SERVICES;
+                        let request = RequestInfo::new("example.com", format!("/svc{}/item", service));
+                        let (_, action) = lb.route("http", &request).unwrap();
+                        match action {
+                            ListenerAction::Forward { target_group } => assert_eq!(target_group, format!("tg-{}", service)),
+                            other => panic!("request for service {} hit {:?}", service, other),
+                        }
+                        let (rule, _) = lb.route("http", &RequestInfo::new("example.com", "/unknown")).unwrap();
+                        assert_eq!(rule, None);
+                        checked.fetch_add(1, Ordering::Relaxed);
+                        n += 1;
+                    }
+                })
+            })
+            .collect();
+
+        let mut order: Vec<String> = (0..SERVICES).map(|i| format!("svc-{}", i)).collect();
+        for round in 0..500 {
+            order.rotate_left(1 + round % (SERVICES - 1));
+            lb.set_rule_priorities("http", order.clone()).await.unwrap();
+            tokio::task::yield_now().await;
+        }
+        stop.store(true, Ordering::Relaxed);
+        for matcher in matchers {
+            matcher.join().expect("matcher observed a wrong action");
+        }
+        assert!(checked.load(Ordering::Relaxed) > 0);
+
+        let rules = lb.list_rules("http").await.unwrap();
+        let priorities: HashSet<i32> = rules.iter().map(|r| r.priority).collect();
+        assert_eq!(priorities.len(), SERVICES);
+        assert_eq!(rules.iter().map(|r| r.id.clone()).collect::<Vec<_>>(), order);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_metrics_match_generated_traffic() {
+        const WORKERS: u64 = 8;
+        const CONNECTIONS: u64 = 250;
+        const REQUESTS_PER_CONNECTION: u64 = 4;
+        const BYTES_PER_REQUEST: u64 = 1500;
+
+        let lb = Arc::new(load_balancer(1).await);
+        lb.add_rule("http", path_rule("svc", 10, "/*", "tg-0")).await.unwrap();
+        let target = |id: &str, status| Target {
+            id: id.into(),
+            target_group_id: "tg-0".into(),
+            target_type: TargetType::IP,
+            port: Some(8080),
+            weight: None,
+            status,
+        };
+        lb.register_targets(
+            "tg-0",
+            vec![
+                target("10.0.0.1", TargetHealth::Healthy),
+                target("10.0.0.2", TargetHealth::Healthy),
+                target("10.0.0.3", TargetHealth::Unhealthy),
+                target("10.0.0.4", TargetHealth::Draining),
+            ],
+        )
+        .await
+        .unwrap();
+
+        let counters = lb.traffic_counters("lb-1").unwrap();
+        let workers: Vec<_> = (0..WORKERS)
+            .map(|_| {
+                let counters = counters.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..CONNECTIONS {
+                        let conn = counters.open_connection();
+                        // Requests cycle through 200, 302, 404, 503: one per class per connection
+                        for status in [200, 302, 404, 503] {
+                            conn.record_request(status);
+                            conn.record_bytes(BYTES_PER_REQUEST);
+                        }
+                    }
+                })
+            })
+            .collect();
+        let open = counters.open_connection();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        let connections = WORKERS * CONNECTIONS;
+        let requests = connections * REQUESTS_PER_CONNECTION;
+        let sample = lb.sample_metrics().await.remove(0);
+        assert_eq!(sample.active_connections, 1);
+        assert_eq!(sample.new_connections as u64, connections + 1);
+        assert_eq!(sample.request_count as u64, requests);
+        assert_eq!(sample.processed_bytes as u64, requests * BYTES_PER_REQUEST);
+        for class in [sample.http_2xx, sample.http_3xx, sample.http_4xx, sample.http_5xx] {
+            assert_eq!(class as u64, connections);
+        }
+        assert_eq!((sample.healthy_host_count, sample.unhealthy_host_count), (2, 1));
+
+        drop(open);
+        let idle = lb.sample_metrics().await.remove(0);
+        assert_eq!((idle.active_connections, idle.request_count, idle.new_connections), (0, 0, 0));
+        let history = lb.get_metrics("lb-1", chrono::Duration::minutes(5)).await.unwrap();
+        assert_eq!(history.len(), 2);
+
+        let admin = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = admin.local_addr().unwrap();
+        tokio::spawn(lb.clone().serve_prometheus(admin));
+        let mut stream = tokio::net::TcpStream::connect(address).await.unwrap();
+        stream.write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n").await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains(&format!("sirsi_lb_requests_total{{load_balancer=\"lb-1\"}} {}", requests)));
+        assert!(response.contains(&format!(
+            "sirsi_lb_responses_total{{load_balancer=\"lb-1\",class=\"5xx\"}} {}",
+            connections
+        )));
+        assert!(response.contains("sirsi_lb_healthy_hosts{load_balancer=\"lb-1\"} 2"));
+    }
+
+    // Answers every request with `status` after `delay`, reporting each request it received
+    async fn upstream(status: u16, body: &'static str, delay: Duration) -> (u16, tokio::sync::mpsc::UnboundedReceiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (seen, received) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let seen = seen.clone();
+                tokio::spawn(async move {
+                    let mut buf = vec![0u8; 4096];
+                    let n = stream.read(&mut buf).await.unwrap_or(0);
+                    let _ = seen.send(String::from_utf8_lossy(&buf[..n]).to_string());
+                    tokio::time::sleep(delay).await;
+                    let response = format!("HTTP/1.1 {} X\r\nContent-Length: {}\r\n\r\n{}", status, body.len(), body);
+                    let _ = stream.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+        (port, received)
+    }
+
+    async fn mirrored_load_balancer(primary: u16, shadow: u16, max_concurrent: usize) -> Arc<SoftwareLoadBalancer> {
+        let lb = Arc::new(load_balancer(2).await);
+        for (group, port) in [("tg-0", primary), ("tg-1", shadow)] {
+            let target = Target {
+                id: "127.0.0.1".into(),
+                target_group_id: group.into(),
+                target_type: TargetType::IP,
+                port: Some(port),
+                weight: None,
+                status: TargetHealth::Healthy,
+            };
+            lb.register_targets(group, vec![target]).await.unwrap();
+        }
+        let rule = ListenerRule {
+            mirror: Some(super::super::TrafficMirror {
+                target_group: "tg-1".into(),
+                percentage: 100.0,
+                max_concurrent,
+                strip_headers: vec!["authorization".into()],
+                tag_header: Some("x-sirsi-mirrored".into()),
+            }),
+            ..path_rule("checkout", 10, "/checkout/*", "tg-0")
+        };
+        lb.add_rule("http", rule).await.unwrap();
+        lb
+    }
+
+    fn checkout_request() -> HttpRequest {
+        HttpRequest::new("POST", "/checkout/cart")
+            .with_header("Host", "shop.example.com")
+            .with_header("Authorization", "Bearer secret")
+            .with_body("{\"items\":1}")
+    }
+
+    #[tokio::test]
+    async fn test_slow_mirror_does_not_delay_primary() {
+        let (primary, mut primary_seen) = upstream(200, "primary", Duration::ZERO).await;
+        let (shadow, mut shadow_seen) = upstream(200, "shadow", Duration::from_secs(5)).await;
+        let lb = mirrored_load_balancer(primary, shadow, 1).await;
+        assert!(matches!(lb.delete_target_group("tg-1").await, Err(NetworkError::Conflict(_))));
+
+        for _ in 0..3 {
+            let started = std::time::Instant::now();
+            let response = lb.forward_http("http", checkout_request()).await.unwrap();
+            assert_eq!((response.status, response.body.as_slice()), (200, b"primary".as_slice()));
+            assert!(started.elapsed() < Duration::from_secs(1));
+            let seen = primary_seen.recv().await.unwrap();
+            assert!(seen.contains("Authorization: Bearer secret"));
+        }
+
+        let shadowed = tokio::time::timeout(Duration::from_secs(1), shadow_seen.recv()).await.unwrap().unwrap();
+        assert!(shadowed.starts_with("POST /checkout/cart HTTP/1.1"));
+        assert!(shadowed.contains("Host: shop.example.com-shadow"));
+        assert!(shadowed.contains("x-sirsi-mirrored: true"));
+        assert!(!shadowed.to_ascii_lowercase().contains("authorization"));
+        assert!(shadowed.ends_with("{\"items\":1}"));
+
+        // One shadow is still in flight, so the cap of one dropped the other two
+        let metrics = lb.mirror_metrics("http").unwrap().remove(0);
+        assert_eq!((metrics.mirrored, metrics.dropped, metrics.completed), (1, 2, 0));
+        let counters = lb.traffic_counters("lb-1").unwrap().snapshot();
+        assert_eq!((counters.request_count, counters.http_2xx), (3, 3));
+    }
+
+    #[tokio::test]
+    async fn test_failing_mirror_is_counted_apart_from_primary() {
+        let (primary, _primary_seen) = upstream(200, "primary", Duration::ZERO).await;
+        let (shadow, mut shadow_seen) = upstream(500, "boom", Duration::ZERO).await;
+        let lb = mirrored_load_balancer(primary, shadow, 10).await;
+
+        for _ in 0..4 {
+            let response = lb.forward_http("http", checkout_request()).await.unwrap();
+            assert_eq!(response.status, 200);
+            shadow_seen.recv().await.unwrap();
+        }
+        let unmatched = lb
+            .forward_http("http", HttpRequest::new("GET", "/missing").with_header("Host", "shop.example.com"))
+            .await
+            .unwrap();
+        assert_eq!(unmatched.status, 404);
+
+        let metrics = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let metrics = lb.mirror_metrics("http").unwrap().remove(0);
+                if metrics.completed + metrics.errors == 4 {
+                    return metrics;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!((metrics.mirrored, metrics.errors), (4, 4));
+        assert_eq!(metrics.error_rate(), 1.0);
+
+        // Editing an unrelated rule keeps the mirror's history
+        lb.add_rule("http", path_rule("other", 20, "/other/*", "tg-0")).await.unwrap();
+        assert_eq!(lb.mirror_metrics("http").unwrap()[0].errors, 4);
+
+        let counters = lb.traffic_counters("lb-1").unwrap().snapshot();
+        assert_eq!((counters.request_count, counters.http_2xx, counters.http_4xx, counters.http_5xx), (5, 4, 1, 0));
+    }
+}
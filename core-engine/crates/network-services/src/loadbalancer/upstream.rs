@@ -0,0 +1,542 @@
+use std::collections::HashMap;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime};
+
+use rustls::client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier};
+use rustls::{Certificate, CertificateError, ClientConfig, PrivateKey, RootCertStore, ServerName};
+use serde::{Deserialize, Serialize};
+use sirsi_key_vault::secret::{SecretManager, SecretValue};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+use tokio::sync::RwLock;
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::TlsConnector;
+use tracing::{info, warn};
+
+use crate::error::{NetworkError, NetworkResult};
+use super::{BackendTls, Target, TargetGroup, TargetHealth};
+
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+// TLS 1.3 servers reject a client certificate after the client has finished its side of the
+// handshake, so the diagnostic waits briefly for a post-handshake alert
+pub const DEFAULT_ALERT_PROBE: Duration = Duration::from_millis(250);
+
+pub enum UpstreamStream {
+    Plain(TcpStream),
+    Tls(Box<TlsStream<TcpStream>>),
+}
+
+impl AsyncRead for UpstreamStream {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Plain(stream) => Pin::new(stream).poll_read(cx, buf),
+            Self::Tls(stream) => Pin::new(stream.as_mut()).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for UpstreamStream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Self::Plain(stream) => Pin::new(stream).poll_write(cx, buf),
+            Self::Tls(stream) => Pin::new(stream.as_mut()).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Plain(stream) => Pin::new(stream).poll_flush(cx),
+            Self::Tls(stream) => Pin::new(stream.as_mut()).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Plain(stream) => Pin::new(stream).poll_shutdown(cx),
+            Self::Tls(stream) => Pin::new(stream.as_mut()).poll_shutdown(cx),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TargetTlsReport {
+    pub target_id: String,
+    pub address: String,
+    pub success: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct HandshakeStats {
+    consecutive_failures: u32,
+    consecutive_successes: u32,
+    total_failures: u64,
+}
+
+// Chain validation without the name check, for `verify_hostname: false`
+struct IgnoreHostname(WebPkiVerifier);
+
+impl ServerCertVerifier for IgnoreHostname {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        match self
+            .0
+            .verify_server_cert(end_entity, intermediates, server_name, scts, ocsp_response, now)
+        {
+            Err(rustls::Error::InvalidCertificate(CertificateError::NotValidForName)) => {
+                Ok(ServerCertVerified::assertion())
+            }
+            other => other,
+        }
+    }
+}
+
+struct CachedConfig {
+    config: Arc<ClientConfig>,
+    // Version of the client certificate secret the config was built from
+    secret_version: Option<i32>,
+}
+
+fn tls_error(group_id: &str, msg: impl std::fmt::Display) -> NetworkError {
+    NetworkError::Config(format!("Backend TLS for target group {}: {}", group_id, msg))
+}
+
+fn parse_certificates(group_id: &str, pem: &str) -> NetworkResult<Vec<Certificate>> {
+    let certs = rustls_pemfile::certs(&mut pem.as_bytes()).map_err(|e| tls_error(group_id, e))?;
+    if certs.is_empty() {
+        return Err(tls_error(group_id, "no certificates found in PEM"));
+    }
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+fn parse_private_key(group_id: &str, pem: &str) -> NetworkResult<PrivateKey> {
+    rustls_pemfile::read_all(&mut pem.as_bytes())
+        .map_err(|e| tls_error(group_id, e))?
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::PKCS8Key(key) | rustls_pemfile::Item::RSAKey(key) | rustls_pemfile::Item::ECKey(key) => {
+                Some(PrivateKey(key))
+            }
+            _ => None,
+        })
+        .ok_or_else(|| tls_error(group_id, "no private key found in PEM"))
+}
+
+// Upstream connections prefer IP targets' own address and fall back to the group port
+pub fn target_address(group: &TargetGroup, target: &Target) -> NetworkResult<SocketAddr> {
+    let ip = target.id.parse().map_err(|_| {
+        NetworkError::Validation(format!("Target {} is not an IP address the load balancer can dial", target.id))
+    })?;
+    Ok(SocketAddr::new(ip, target.port.unwrap_or(group.port)))
+}
+
+pub struct UpstreamConnector {
+    secrets: Option<Arc<dyn SecretManager>>,
+    configs: RwLock<HashMap<String, CachedConfig>>,
+    handshakes: Mutex<HashMap<(String, String), HandshakeStats>>,
+    connect_timeout: Duration,
+    alert_probe: Duration,
+}
+
+impl Default for UpstreamConnector {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl UpstreamConnector {
+    pub fn new(secrets: Option<Arc<dyn SecretManager>>) -> Self {
+        Self {
+            secrets,
+            configs: RwLock::new(HashMap::new()),
+            handshakes: Mutex::new(HashMap::new()),
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            alert_probe: DEFAULT_ALERT_PROBE,
+        }
+    }
+
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    pub fn with_alert_probe(mut self, probe: Duration) -> Self {
+        self.alert_probe = probe;
+        self
+    }
+
+    async fn client_identity(&self, group_id: &str, tls: &BackendTls) -> NetworkResult<Option<(Vec<Certificate>, PrivateKey, i32)>> {
+        let Some(secret_id) = &tls.client_certificate_secret else {
+            return Ok(None);
+        };
+        let secrets = self
+            .secrets
+            .as_ref()
+            .ok_or_else(|| tls_error(group_id, "client certificates need a key-vault secret manager"))?;
+        let secret = secrets
+            .get_secret(secret_id)
+            .await
+            .map_err(|e| tls_error(group_id, format!("failed to load client certificate {}: {}", secret_id, e)))?;
+        let SecretValue::Certificate(cert) = secret.value else {
+            return Err(tls_error(group_id, format!("secret {} is not a certificate", secret_id)));
+        };
+        let mut chain = parse_certificates(group_id, &cert.certificate)?;
+        for intermediate in cert.chain.iter().flatten() {
+            chain.extend(parse_certificates(group_id, intermediate)?);
+        }
+        Ok(Some((chain, parse_private_key(group_id, &cert.private_key)?, secret.version)))
+    }
+
+    async fn build_config(&self, group_id: &str, tls: &BackendTls) -> NetworkResult<CachedConfig> {
+        let mut roots = RootCertStore::empty();
+        for cert in parse_certificates(group_id, &tls.server_ca_bundle)? {
+            roots.add(&cert).map_err(|e| tls_error(group_id, e))?;
+        }
+
+        let verifier = WebPkiVerifier::new(roots, None);
+        let verifier: Arc<dyn ServerCertVerifier> = if tls.verify_hostname {
+            Arc::new(verifier)
+        } else {
+            Arc::new(IgnoreHostname(verifier))
+        };
+        let builder = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_custom_certificate_verifier(verifier);
+        let (config, secret_version) = match self.client_identity(group_id, tls).await? {
+            Some((chain, key, version)) => (
+                builder.with_client_auth_cert(chain, key).map_err(|e| tls_error(group_id, e))?,
+                Some(version),
+            ),
+            None => (builder.with_no_client_auth(), None),
+        };
+        Ok(CachedConfig {
+            config: Arc::new(config),
+            secret_version,
+        })
+    }
+
+    async fn config_for(&self, group_id: &str, tls: &BackendTls) -> NetworkResult<Arc<ClientConfig>> {
+        if let Some(cached) = self.configs.read().await.get(group_id) {
+            return Ok(cached.config.clone());
+        }
+        let built = self.build_config(group_id, tls).await?;
+        let config = built.config.clone();
+        self.configs.write().await.insert(group_id.to_string(), built);
+        Ok(config)
+    }
+
+    pub async fn invalidate(&self, group_id: &str) {
+        self.configs.write().await.remove(group_id);
+    }
+
+    // Rebuilds configs whose client certificate secret has a newer version; returns reloaded groups
+    pub async fn reload_rotated(&self, groups: &[TargetGroup]) -> NetworkResult<Vec<String>> {
+        let mut reloaded = Vec::new();
+        for group in groups {
+            let Some(tls) = &group.backend_tls else { continue };
+            let (Some(secret_id), Some(secrets)) = (&tls.client_certificate_secret, &self.secrets) else {
+                continue;
+            };
+            let cached_version = match self.configs.read().await.get(&group.id) {
+                Some(cached) => cached.secret_version,
+                None => continue,
+            };
+            let current = secrets
+                .get_secret(secret_id)
+                .await
+                .map_err(|e| tls_error(&group.id, format!("failed to check client certificate {}: {}", secret_id, e)))?;
+            if cached_version != Some(current.version) {
+                let rebuilt = self.build_config(&group.id, tls).await?;
+                self.configs.write().await.insert(group.id.clone(), rebuilt);
+                info!("Reloaded backend client certificate for target group {}", group.id);
+                reloaded.push(group.id.clone());
+            }
+        }
+        Ok(reloaded)
+    }
+
+    pub async fn connect(&self, group: &TargetGroup, target: &Target) -> NetworkResult<UpstreamStream> {
+        let address = target_address(group, target)?;
+        let tcp = tokio::time::timeout(self.connect_timeout, TcpStream::connect(address))
+            .await
+            .map_err(|_| NetworkError::Unavailable(format!("Timed out connecting to {}", address)))?
+            .map_err(|e| NetworkError::Unavailable(format!("Failed to connect to {}: {}", address, e)))?;
+        let Some(tls) = &group.backend_tls else {
+            return Ok(UpstreamStream::Plain(tcp));
+        };
+
+        let config = self.config_for(&group.id, tls).await?;
+        let name = tls.sni_override.clone().unwrap_or_else(|| address.ip().to_string());
+        let server_name = ServerName::try_from(name.as_str())
+            .map_err(|_| tls_error(&group.id, format!("invalid server name {}", name)))?;
+        let stream = tokio::time::timeout(self.connect_timeout, TlsConnector::from(config).connect(server_name, tcp))
+            .await
+            .map_err(|_| NetworkError::Unavailable(format!("TLS handshake with {} timed out", address)))?
+            .map_err(|e| NetworkError::LoadBalancer(format!("TLS handshake with {} failed: {}", address, e)))?;
+        Ok(UpstreamStream::Tls(Box::new(stream)))
+    }
+
+    // Handshake plus a short wait for a post-handshake rejection alert
+    pub async fn probe(&self, group: &TargetGroup, target: &Target) -> NetworkResult<()> {
+        let mut stream = self.connect(group, target).await?;
+        if let UpstreamStream::Tls(_) = stream {
+            let mut byte = [0u8; 1];
+            if let Ok(Err(e)) = tokio::time::timeout(self.alert_probe, stream.read(&mut byte)).await {
+                return Err(NetworkError::LoadBalancer(format!("TLS handshake with {} failed: {}", target.id, e)));
+            }
+        }
+        Ok(())
+    }
+
+    // Returns the health the target should move to once a failure or recovery streak crosses
+    // the group's health check thresholds
+    pub fn record_handshake(&self, group: &TargetGroup, target: &Target, success: bool) -> Option<TargetHealth> {
+        let mut handshakes = self.handshakes.lock().expect("handshake stats lock poisoned");
+        let stats = handshakes.entry((group.id.clone(), target.id.clone())).or_default();
+        if success {
+            stats.consecutive_failures = 0;
+            stats.consecutive_successes += 1;
+            let recovered = stats.consecutive_successes >= group.health_check.healthy_threshold.max(1) as u32;
+            (recovered && matches!(target.status, TargetHealth::Unhealthy)).then_some(TargetHealth::Healthy)
+        } else {
+            stats.consecutive_successes = 0;
+            stats.consecutive_failures += 1;
+            stats.total_failures += 1;
+            warn!(
+                "TLS handshake to target {} in {} failed ({} in a row)",
+                target.id, group.id, stats.consecutive_failures
+            );
+            let failed = stats.consecutive_failures >= group.health_check.unhealthy_threshold.max(1) as u32;
+            (failed && !matches!(target.status, TargetHealth::Unhealthy)).then_some(TargetHealth::Unhealthy)
+        }
+    }
+
+    pub fn handshake_failures(&self, group_id: &str, target_id: &str) -> u64 {
+        self.handshakes
+            .lock()
+            .expect("handshake stats lock poisoned")
+            .get(&(group_id.to_string(), target_id.to_string()))
+            .map(|stats| stats.total_failures)
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loadbalancer::{
+        HealthCheck, ListenerProtocol, LoadBalancingAlgorithm, SoftwareLoadBalancer, TargetGroupAttributes,
+        TargetGroupManager, TargetType,
+    };
+    use chrono::Utc;
+    use rcgen::{BasicConstraints, CertificateParams, IsCa};
+    use rustls::server::AllowAnyAuthenticatedClient;
+    use sirsi_key_vault::secret::{CertificateSecret, InMemorySecretManager, Secret};
+    use tokio::net::TcpListener;
+    use tokio_rustls::TlsAcceptor;
+
+    struct Pki {
+        ca: rcgen::Certificate,
+    }
+
+    impl Pki {
+        fn new() -> Self {
+            let mut params = CertificateParams::new(vec![]);
+            params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+            Self {
+                ca: rcgen::Certificate::from_params(params).unwrap(),
+            }
+        }
+
+        fn ca_pem(&self) -> String {
+            self.ca.serialize_pem().unwrap()
+        }
+
+        // Returns (certificate PEM, private key PEM)
+        fn issue(&self, names: &[&str], expired: bool) -> (String, String) {
+            let mut params = CertificateParams::new(names.iter().map(|n| n.to_string()).collect::<Vec<_>>());
+            if expired {
+                params.not_before = rcgen::date_time_ymd(2019, 1, 1);
+                params.not_after = rcgen::date_time_ymd(2020, 1, 1);
+            }
+            let cert = rcgen::Certificate::from_params(params).unwrap();
+            (cert.serialize_pem_with_signer(&self.ca).unwrap(), cert.serialize_private_key_pem())
+        }
+    }
+
+    // mTLS backend that requires a client certificate from the test CA and holds connections open
+    async fn start_backend(pki: &Pki) -> u16 {
+        let (cert, key) = pki.issue(&["backend.internal"], false);
+        let mut roots = RootCertStore::empty();
+        roots.add(&parse_certificates("test", &pki.ca_pem()).unwrap()[0]).unwrap();
+        let config = rustls::ServerConfig::builder()
+            .with_safe_defaults()
+            .with_client_cert_verifier(Arc::new(AllowAnyAuthenticatedClient::new(roots)))
+            .with_single_cert(parse_certificates("test", &cert).unwrap(), parse_private_key("test", &key).unwrap())
+            .unwrap();
+        let acceptor = TlsAcceptor::from(Arc::new(config));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            loop {
+                let (tcp, _) = listener.accept().await.unwrap();
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move {
+                    if let Ok(mut stream) = acceptor.accept(tcp).await {
+                        let mut buf = [0u8; 64];
+                        while matches!(stream.read(&mut buf).await, Ok(n) if n > 0) {}
+                    }
+                });
+            }
+        });
+        port
+    }
+
+    fn certificate_secret(id: &str, (certificate, private_key): (String, String)) -> Secret {
+        Secret {
+            id: id.into(),
+            name: id.into(),
+            description: None,
+            value: SecretValue::Certificate(CertificateSecret {
+                certificate,
+                private_key,
+                chain: None,
+            }),
+            version: 0,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            expires_at: None,
+            metadata: HashMap::new(),
+            labels: HashMap::new(),
+            rotation_policy: None,
+        }
+    }
+
+    fn group(id: &str, port: u16, tls: BackendTls) -> TargetGroup {
+        TargetGroup {
+            id: id.into(),
+            name: id.into(),
+            protocol: ListenerProtocol::HTTPS,
+            port,
+            target_type: TargetType::IP,
+            vpc_id: "vpc-1".into(),
+            health_check: HealthCheck {
+                protocol: ListenerProtocol::HTTPS,
+                port: None,
+                path: None,
+                interval_seconds: 10,
+                timeout_seconds: 5,
+                healthy_threshold: 2,
+                unhealthy_threshold: 2,
+            },
+            attributes: TargetGroupAttributes {
+                deregistration_delay_seconds: 0,
+                stickiness_enabled: false,
+                stickiness_type: None,
+                stickiness_duration_seconds: None,
+                load_balancing_algorithm: LoadBalancingAlgorithm::RoundRobin,
+            },
+            backend_tls: Some(tls),
+        }
+    }
+
+    async fn setup() -> (Pki, Arc<InMemorySecretManager>, SoftwareLoadBalancer, u16) {
+        let pki = Pki::new();
+        let port = start_backend(&pki).await;
+        let secrets = Arc::new(InMemorySecretManager::new());
+        secrets
+            .create_secret(certificate_secret("lb/client", pki.issue(&["lb.internal"], false)))
+            .await
+            .unwrap();
+        let lb = SoftwareLoadBalancer::with_connector(
+            UpstreamConnector::new(Some(secrets.clone())).with_alert_probe(Duration::from_millis(500)),
+        );
+        (pki, secrets, lb, port)
+    }
+
+    async fn add_group(lb: &SoftwareLoadBalancer, pki: &Pki, id: &str, port: u16, sni: &str, verify_hostname: bool) {
+        let tls = BackendTls {
+            server_ca_bundle: pki.ca_pem(),
+            client_certificate_secret: Some("lb/client".into()),
+            sni_override: Some(sni.into()),
+            verify_hostname,
+        };
+        lb.create_target_group(group(id, port, tls)).await.unwrap();
+        lb.register_targets(
+            id,
+            vec![Target {
+                id: "127.0.0.1".into(),
+                target_group_id: id.into(),
+                target_type: TargetType::IP,
+                port: None,
+                weight: None,
+                status: TargetHealth::Healthy,
+            }],
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_hostname_verification() {
+        let (pki, _, lb, port) = setup().await;
+        add_group(&lb, &pki, "tg-ok", port, "backend.internal", true).await;
+        add_group(&lb, &pki, "tg-mismatch", port, "other.internal", true).await;
+        add_group(&lb, &pki, "tg-unverified", port, "other.internal", false).await;
+
+        let ok = lb.validate_backend_tls("tg-ok").await.unwrap();
+        assert!(ok[0].success, "{:?}", ok[0].error);
+        assert!(lb.connect_upstream("tg-ok", "127.0.0.1").await.is_ok());
+
+        let mismatch = lb.validate_backend_tls("tg-mismatch").await.unwrap();
+        assert!(!mismatch[0].success);
+        assert!(mismatch[0].error.as_deref().unwrap().contains("NotValidForName"));
+
+        let unverified = lb.validate_backend_tls("tg-unverified").await.unwrap();
+        assert!(unverified[0].success, "{:?}", unverified[0].error);
+    }
+
+    #[tokio::test]
+    async fn test_expired_client_cert_marks_target_unhealthy_until_rotation() {
+        let (pki, secrets, lb, port) = setup().await;
+        add_group(&lb, &pki, "tg-api", port, "backend.internal", true).await;
+        assert!(lb.validate_backend_tls("tg-api").await.unwrap()[0].success);
+
+        // Rotating to an expired certificate is picked up without rebuilding the load balancer
+        secrets
+            .update_secret(certificate_secret("lb/client", pki.issue(&["lb.internal"], true)))
+            .await
+            .unwrap();
+        assert_eq!(lb.reload_backend_certificates().await.unwrap(), vec!["tg-api".to_string()]);
+        assert!(lb.reload_backend_certificates().await.unwrap().is_empty());
+
+        for _ in 0..2 {
+            let report = lb.validate_backend_tls("tg-api").await.unwrap();
+            assert!(!report[0].success);
+        }
+        let targets = lb.describe_target_health("tg-api").await.unwrap();
+        assert!(matches!(targets[0].status, TargetHealth::Unhealthy));
+
+        secrets
+            .update_secret(certificate_secret("lb/client", pki.issue(&["lb.internal"], false)))
+            .await
+            .unwrap();
+        lb.reload_backend_certificates().await.unwrap();
+        for _ in 0..2 {
+            assert!(lb.validate_backend_tls("tg-api").await.unwrap()[0].success);
+        }
+        let targets = lb.describe_target_health("tg-api").await.unwrap();
+        assert!(matches!(targets[0].status, TargetHealth::Healthy));
+    }
+}
@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde_json::{json, Value};
+
+use super::{
+    missing, text, CloudApi, HttpMethod, ManagedRoute, NetworkEndpoint, NetworkProvider, NextHop, PeeringProvider, PeeringState,
+    RouteEntry,
+};
+use crate::error::NetworkResult;
+
+// VPC peering through the EC2 API. EC2 is a query API: `path` names the region and action,
+// and the transport sends `body` as its parameters and hands the response back as JSON.
+pub struct AwsPeering {
+    api: Arc<dyn CloudApi>,
+    // Credentials for other accounts, used to accept peerings and edit their route tables
+    accounts: HashMap<String, Arc<dyn CloudApi>>,
+}
+
+impl AwsPeering {
+    pub fn new(api: Arc<dyn CloudApi>) -> Self {
+        Self { api, accounts: HashMap::new() }
+    }
+
+    pub fn with_account(mut self, account: impl Into<String>, api: Arc<dyn CloudApi>) -> Self {
+        self.accounts.insert(account.into(), api);
+        self
+    }
+
+    async fn ec2(&self, endpoint: &NetworkEndpoint, action: &str, params: Value) -> NetworkResult<Option<Value>> {
+        let api = self.accounts.get(&endpoint.account).unwrap_or(&self.api);
+        api.call(HttpMethod::Post, &format!("/{}/ec2/{}", endpoint.region, action), Some(params)).await
+    }
+}
+
+#[async_trait]
+impl PeeringProvider for AwsPeering {
+    fn provider(&self) -> NetworkProvider {
+        NetworkProvider::Aws
+    }
+
+    fn exchanges_routes(&self) -> bool {
+        false
+    }
+
+    // Requested from the local side and accepted from the remote one
+    async fn create_peering(&self, name: &str, local: &NetworkEndpoint, remote: &NetworkEndpoint) -> NetworkResult<String> {
+        let params = json!({
+            "VpcId": local.network_id,
+            "PeerVpcId": remote.network_id,
+            "PeerOwnerId": remote.account,
+            "PeerRegion": remote.region,
+            "TagSpecification.1.ResourceType": "vpc-peering-connection",
+            "TagSpecification.1.Tag.1.Key": "Name",
+            "TagSpecification.1.Tag.1.Value": name,
+        });
+        let created = self.ec2(local, "CreateVpcPeeringConnection", params).await?.unwrap_or_default();
+        let id = text(&created, &["VpcPeeringConnection", "VpcPeeringConnectionId"]).ok_or_else(|| missing("VpcPeeringConnectionId"))?;
+        self.ec2(remote, "AcceptVpcPeeringConnection", json!({ "VpcPeeringConnectionId": id })).await?;
+        Ok(id)
+    }
+
+    async fn peering_state(&self, id: &str, local: &NetworkEndpoint, _remote: &NetworkEndpoint) -> NetworkResult<PeeringState> {
+        let described = self
+            .ec2(local, "DescribeVpcPeeringConnections", json!({ "VpcPeeringConnectionId.1": id }))
+            .await?
+            .unwrap_or_default();
+        let Some(connection) = described["VpcPeeringConnections"].get(0) else {
+            return Ok(PeeringState::Failed(format!("{} no longer exists", id)));
+        };
+        let message = text(connection, &["Status", "Message"]).unwrap_or_default();
+        Ok(match text(connection, &["Status", "Code"]).as_deref() {
+            Some("active") => PeeringState::Active,
+            Some("initiating-request" | "pending-acceptance" | "provisioning") => PeeringState::Pending,
+            Some(code) => PeeringState::Failed(format!("{}: {}", code, message)),
+            None => return Err(missing("Status.Code")),
+        })
+    }
+
+    async fn delete_peering(&self, id: &str, local: &NetworkEndpoint, _remote: &NetworkEndpoint) -> NetworkResult<()> {
+        self.ec2(local, "DeleteVpcPeeringConnection", json!({ "VpcPeeringConnectionId": id })).await.map(|_| ())
+    }
+
+    async fn list_routes(&self, endpoint: &NetworkEndpoint, route_table: &str) -> NetworkResult<Vec<RouteEntry>> {
+        let described = self
+            .ec2(endpoint, "DescribeRouteTables", json!({ "RouteTableId.1": route_table }))
+            .await?
+            .unwrap_or_default();
+        let routes = described["RouteTables"].get(0).and_then(|t| t["Routes"].as_array()).cloned().unwrap_or_default();
+        Ok(routes
+            .iter()
+            .filter_map(|route| {
+                let next_hop = ["VpcPeeringConnectionId", "GatewayId", "TransitGatewayId", "NatGatewayId", "NetworkInterfaceId"]
+                    .iter()
+                    .find_map(|field| text(route, &[field]));
+                Some(RouteEntry { destination_cidr: text(route, &["DestinationCidrBlock"])?, next_hop })
+            })
+            .collect())
+    }
+
+    async fn add_route(&self, endpoint: &NetworkEndpoint, route: &ManagedRoute) -> NetworkResult<()> {
+        let mut params = json!({ "RouteTableId": route.route_table, "DestinationCidrBlock": route.destination_cidr });
+        match &route.next_hop {
+            NextHop::Peering(id) => params["VpcPeeringConnectionId"] = json!(id),
+            NextHop::VpnGateway(id) => params["GatewayId"] = json!(id),
+        }
+        self.ec2(endpoint, "CreateRoute", params).await.map(|_| ())
+    }
+
+    async fn remove_route(&self, endpoint: &NetworkEndpoint, route: &ManagedRoute) -> NetworkResult<()> {
+        let params = json!({ "RouteTableId": route.route_table, "DestinationCidrBlock": route.destination_cidr });
+        self.ec2(endpoint, "DeleteRoute", params).await.map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::peering::testing::ScriptedApi;
+    use crate::peering::PeeringSide;
+
+    #[tokio::test]
+    async fn test_cross_account_peering_is_accepted_by_the_peer() {
+        let requester = Arc::new(ScriptedApi::default());
+        requester.respond(
+            HttpMethod::Post,
+            "/us-east-1/ec2/CreateVpcPeeringConnection",
+            Some(json!({ "VpcPeeringConnection": { "VpcPeeringConnectionId": "pcx-0abc" } })),
+        );
+        let accepter = Arc::new(ScriptedApi::default());
+        accepter.respond(
+            HttpMethod::Post,
+            "/eu-west-1/ec2/DescribeRouteTables",
+            Some(json!({ "RouteTables": [{ "Routes": [
+                { "DestinationCidrBlock": "10.9.0.0/16", "GatewayId": "local" },
+                { "DestinationCidrBlock": "10.0.0.0/16", "VpcPeeringConnectionId": "pcx-0abc" },
+            ] }] })),
+        );
+        let aws = AwsPeering::new(requester.clone()).with_account("444455556666", accepter.clone());
+        let local = NetworkEndpoint::new(NetworkProvider::Aws, "111122223333", "us-east-1", "vpc-a");
+        let remote = NetworkEndpoint::new(NetworkProvider::Aws, "444455556666", "eu-west-1", "vpc-b");
+
+        assert_eq!(aws.create_peering("a-b", &local, &remote).await.unwrap(), "pcx-0abc");
+        let calls = accepter.calls.lock().unwrap().clone();
+        assert_eq!(calls[0].1, "/eu-west-1/ec2/AcceptVpcPeeringConnection");
+        assert_eq!(calls[0].2.as_ref().unwrap()["VpcPeeringConnectionId"], "pcx-0abc");
+
+        let routes = aws.list_routes(&remote, "rtb-b").await.unwrap();
+        assert_eq!(routes[1], RouteEntry { destination_cidr: "10.0.0.0/16".into(), next_hop: Some("pcx-0abc".into()) });
+        let route = ManagedRoute {
+            side: PeeringSide::Remote,
+            route_table: "rtb-b".into(),
+            destination_cidr: "10.0.0.0/16".into(),
+            next_hop: NextHop::Peering("pcx-0abc".into()),
+        };
+        aws.add_route(&remote, &route).await.unwrap();
+        let (_, path, body) = accepter.calls.lock().unwrap().last().cloned().unwrap();
+        assert_eq!(path, "/eu-west-1/ec2/CreateRoute");
+        assert_eq!(body.unwrap(), json!({ "RouteTableId": "rtb-b", "DestinationCidrBlock": "10.0.0.0/16", "VpcPeeringConnectionId": "pcx-0abc" }));
+    }
+}
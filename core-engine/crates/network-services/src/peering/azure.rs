@@ -0,0 +1,146 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde_json::json;
+
+use super::{
+    route_name, text, CloudApi, HttpMethod, ManagedRoute, NetworkEndpoint, NetworkProvider, NextHop, PeeringProvider,
+    PeeringState, RouteEntry,
+};
+use crate::error::{NetworkError, NetworkResult};
+
+const API_VERSION: &str = "2023-05-01";
+
+// VNet peering through Azure Resource Manager. Like GCP, each virtual network carries its own
+// half of the peering and subnet routes are exchanged by the platform; route tables (UDRs)
+// are only written for VPN links, where the next hop is the virtual network gateway.
+pub struct AzurePeering {
+    api: Arc<dyn CloudApi>,
+}
+
+impl AzurePeering {
+    pub fn new(api: Arc<dyn CloudApi>) -> Self {
+        Self { api }
+    }
+
+    fn peering_path(endpoint: &NetworkEndpoint, name: &str) -> String {
+        format!("{}/virtualNetworkPeerings/{}?api-version={}", endpoint.network_id, name, API_VERSION)
+    }
+
+    fn route_path(route: &ManagedRoute) -> String {
+        format!(
+            "{}/routes/{}?api-version={}",
+            route.route_table,
+            route_name(&route.next_hop, &route.destination_cidr),
+            API_VERSION
+        )
+    }
+}
+
+#[async_trait]
+impl PeeringProvider for AzurePeering {
+    fn provider(&self) -> NetworkProvider {
+        NetworkProvider::Azure
+    }
+
+    fn exchanges_routes(&self) -> bool {
+        true
+    }
+
+    async fn create_peering(&self, name: &str, local: &NetworkEndpoint, remote: &NetworkEndpoint) -> NetworkResult<String> {
+        for (from, to) in [(local, remote), (remote, local)] {
+            let body = json!({
+                "properties": {
+                    "remoteVirtualNetwork": { "id": to.network_id },
+                    "allowVirtualNetworkAccess": true,
+                    "allowForwardedTraffic": false,
+                    "allowGatewayTransit": false,
+                    "useRemoteGateways": false,
+                }
+            });
+            self.api.call(HttpMethod::Put, &Self::peering_path(from, name), Some(body)).await?;
+        }
+        Ok(name.to_string())
+    }
+
+    async fn peering_state(&self, id: &str, local: &NetworkEndpoint, remote: &NetworkEndpoint) -> NetworkResult<PeeringState> {
+        for endpoint in [local, remote] {
+            let Some(peering) = self.api.call(HttpMethod::Get, &Self::peering_path(endpoint, id), None).await? else {
+                return Ok(PeeringState::Failed(format!("{} has no peering {}", endpoint.label(), id)));
+            };
+            if text(&peering, &["properties", "provisioningState"]).as_deref() == Some("Failed") {
+                return Ok(PeeringState::Failed(format!("peering {} on {} failed to provision", id, endpoint.label())));
+            }
+            match text(&peering, &["properties", "peeringState"]).as_deref() {
+                Some("Connected") => {}
+                // The other half was deleted; this one will never connect again
+                Some("Disconnected") => return Ok(PeeringState::Failed(format!("peering {} on {} is disconnected", id, endpoint.label()))),
+                _ => return Ok(PeeringState::Pending),
+            }
+        }
+        Ok(PeeringState::Active)
+    }
+
+    async fn delete_peering(&self, id: &str, local: &NetworkEndpoint, remote: &NetworkEndpoint) -> NetworkResult<()> {
+        for endpoint in [local, remote] {
+            self.api.call(HttpMethod::Delete, &Self::peering_path(endpoint, id), None).await?;
+        }
+        Ok(())
+    }
+
+    async fn list_routes(&self, _endpoint: &NetworkEndpoint, route_table: &str) -> NetworkResult<Vec<RouteEntry>> {
+        let path = format!("{}?api-version={}", route_table, API_VERSION);
+        let table = self
+            .api
+            .call(HttpMethod::Get, &path, None)
+            .await?
+            .ok_or_else(|| NetworkError::NotFound(format!("Route table {} not found", route_table)))?;
+        Ok(table["properties"]["routes"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|route| {
+                let next_hop = text(route, &["properties", "nextHopIpAddress"]).or_else(|| text(route, &["properties", "nextHopType"]));
+                Some(RouteEntry { destination_cidr: text(route, &["properties", "addressPrefix"])?, next_hop })
+            })
+            .collect())
+    }
+
+    async fn add_route(&self, _endpoint: &NetworkEndpoint, route: &ManagedRoute) -> NetworkResult<()> {
+        if !matches!(route.next_hop, NextHop::VpnGateway(_)) {
+            return Err(NetworkError::Validation("Azure peerings exchange routes; only VPN routes are added".into()));
+        }
+        let body = json!({ "properties": { "addressPrefix": route.destination_cidr, "nextHopType": "VirtualNetworkGateway" } });
+        self.api.call(HttpMethod::Put, &Self::route_path(route), Some(body)).await.map(|_| ())
+    }
+
+    async fn remove_route(&self, _endpoint: &NetworkEndpoint, route: &ManagedRoute) -> NetworkResult<()> {
+        self.api.call(HttpMethod::Delete, &Self::route_path(route), None).await.map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::peering::testing::ScriptedApi;
+
+    #[tokio::test]
+    async fn test_disconnected_half_fails_the_peering() {
+        let api = Arc::new(ScriptedApi::default());
+        let azure = AzurePeering::new(api.clone());
+        let vnet = |name: &str| format!("/subscriptions/sub-1/resourceGroups/net/providers/Microsoft.Network/virtualNetworks/{}", name);
+        let local = NetworkEndpoint::new(NetworkProvider::Azure, "sub-1", "eastus", vnet("hub"));
+        let remote = NetworkEndpoint::new(NetworkProvider::Azure, "sub-1", "eastus", vnet("spoke"));
+        azure.create_peering("hub-spoke", &local, &remote).await.unwrap();
+        let (method, path, body) = api.calls.lock().unwrap()[0].clone();
+        assert_eq!(method, HttpMethod::Put);
+        assert_eq!(path, format!("{}/virtualNetworkPeerings/hub-spoke?api-version={}", vnet("hub"), API_VERSION));
+        assert_eq!(body.unwrap()["properties"]["remoteVirtualNetwork"]["id"], vnet("spoke"));
+
+        let state = |state: &str| Some(json!({ "properties": { "peeringState": state, "provisioningState": "Succeeded" } }));
+        api.respond(HttpMethod::Get, &AzurePeering::peering_path(&local, "hub-spoke"), state("Connected"));
+        api.respond(HttpMethod::Get, &AzurePeering::peering_path(&remote, "hub-spoke"), state("Disconnected"));
+        assert!(matches!(azure.peering_state("hub-spoke", &local, &remote).await.unwrap(), PeeringState::Failed(_)));
+    }
+}
@@ -0,0 +1,165 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde_json::{json, Value};
+
+use super::{
+    route_name, text, CloudApi, HttpMethod, ManagedRoute, NetworkEndpoint, NetworkProvider, NextHop, PeeringProvider,
+    PeeringState, RouteEntry,
+};
+use crate::error::{NetworkError, NetworkResult};
+
+// VPC Network Peering through the Compute Engine API. A peering is a pair of half-peerings,
+// one added on each network under the same name, and it carries subnet routes by itself.
+// Routes only exist for VPN links, and belong to the network rather than a table, so the
+// route table name is ignored.
+pub struct GcpPeering {
+    api: Arc<dyn CloudApi>,
+}
+
+impl GcpPeering {
+    pub fn new(api: Arc<dyn CloudApi>) -> Self {
+        Self { api }
+    }
+
+    fn network_path(endpoint: &NetworkEndpoint) -> String {
+        format!("/compute/v1/projects/{}/global/networks/{}", endpoint.account, endpoint.network_id)
+    }
+
+    fn network_url(endpoint: &NetworkEndpoint) -> String {
+        format!("https://www.googleapis.com{}", Self::network_path(endpoint))
+    }
+
+    async fn half_peering_state(&self, name: &str, endpoint: &NetworkEndpoint) -> NetworkResult<Option<(String, String)>> {
+        let network = self.api.call(HttpMethod::Get, &Self::network_path(endpoint), None).await?.unwrap_or_default();
+        Ok(network["peerings"].as_array().and_then(|peerings| {
+            peerings.iter().find(|p| p["name"] == name).map(|p| {
+                (text(p, &["state"]).unwrap_or_default(), text(p, &["stateDetails"]).unwrap_or_default())
+            })
+        }))
+    }
+}
+
+#[async_trait]
+impl PeeringProvider for GcpPeering {
+    fn provider(&self) -> NetworkProvider {
+        NetworkProvider::Gcp
+    }
+
+    fn exchanges_routes(&self) -> bool {
+        true
+    }
+
+    async fn create_peering(&self, name: &str, local: &NetworkEndpoint, remote: &NetworkEndpoint) -> NetworkResult<String> {
+        for (from, to) in [(local, remote), (remote, local)] {
+            let body = json!({
+                "networkPeering": {
+                    "name": name,
+                    "network": Self::network_url(to),
+                    "exchangeSubnetRoutes": true,
+                    "importCustomRoutes": false,
+                    "exportCustomRoutes": false,
+                }
+            });
+            self.api.call(HttpMethod::Post, &format!("{}/addPeering", Self::network_path(from)), Some(body)).await?;
+        }
+        Ok(name.to_string())
+    }
+
+    // Active only once both halves are
+    async fn peering_state(&self, id: &str, local: &NetworkEndpoint, remote: &NetworkEndpoint) -> NetworkResult<PeeringState> {
+        for endpoint in [local, remote] {
+            match self.half_peering_state(id, endpoint).await? {
+                Some((state, _)) if state == "ACTIVE" => {}
+                Some((_, details)) if details.is_empty() => return Ok(PeeringState::Pending),
+                Some((_, details)) => return Ok(PeeringState::Failed(details)),
+                None => return Ok(PeeringState::Failed(format!("{} has no peering {}", endpoint.label(), id))),
+            }
+        }
+        Ok(PeeringState::Active)
+    }
+
+    async fn delete_peering(&self, id: &str, local: &NetworkEndpoint, remote: &NetworkEndpoint) -> NetworkResult<()> {
+        for endpoint in [local, remote] {
+            let path = format!("{}/removePeering", Self::network_path(endpoint));
+            match self.api.call(HttpMethod::Post, &path, Some(json!({ "name": id }))).await {
+                Ok(_) | Err(NetworkError::NotFound(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    async fn list_routes(&self, endpoint: &NetworkEndpoint, _route_table: &str) -> NetworkResult<Vec<RouteEntry>> {
+        let path = format!(
+            "/compute/v1/projects/{}/global/routes?filter=network%3D%22{}%22",
+            endpoint.account,
+            Self::network_url(endpoint)
+        );
+        let routes = self.api.call(HttpMethod::Get, &path, None).await?.unwrap_or_default();
+        Ok(routes["items"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|route: &Value| {
+                let next_hop = ["nextHopVpnTunnel", "nextHopGateway", "nextHopPeering", "nextHopIp", "nextHopInstance"]
+                    .iter()
+                    .find_map(|field| text(route, &[field]));
+                Some(RouteEntry { destination_cidr: text(route, &["destRange"])?, next_hop })
+            })
+            .collect())
+    }
+
+    async fn add_route(&self, endpoint: &NetworkEndpoint, route: &ManagedRoute) -> NetworkResult<()> {
+        let NextHop::VpnGateway(tunnel) = &route.next_hop else {
+            return Err(NetworkError::Validation("GCP peerings exchange routes; only VPN routes are added".into()));
+        };
+        let body = json!({
+            "name": route_name(&route.next_hop, &route.destination_cidr),
+            "network": Self::network_url(endpoint),
+            "destRange": route.destination_cidr,
+            "nextHopVpnTunnel": tunnel,
+            "priority": 1000,
+        });
+        let path = format!("/compute/v1/projects/{}/global/routes", endpoint.account);
+        self.api.call(HttpMethod::Post, &path, Some(body)).await.map(|_| ())
+    }
+
+    async fn remove_route(&self, endpoint: &NetworkEndpoint, route: &ManagedRoute) -> NetworkResult<()> {
+        let path = format!(
+            "/compute/v1/projects/{}/global/routes/{}",
+            endpoint.account,
+            route_name(&route.next_hop, &route.destination_cidr)
+        );
+        self.api.call(HttpMethod::Delete, &path, None).await.map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::peering::testing::ScriptedApi;
+
+    #[tokio::test]
+    async fn test_peering_is_active_once_both_halves_are() {
+        let api = Arc::new(ScriptedApi::default());
+        let gcp = GcpPeering::new(api.clone());
+        let local = NetworkEndpoint::new(NetworkProvider::Gcp, "shop-prod", "us-east1", "shop");
+        let remote = NetworkEndpoint::new(NetworkProvider::Gcp, "data-prod", "us-east1", "warehouse");
+        gcp.create_peering("shop-warehouse", &local, &remote).await.unwrap();
+        let calls = api.calls.lock().unwrap().clone();
+        assert_eq!(calls[1].1, "/compute/v1/projects/data-prod/global/networks/warehouse/addPeering");
+        assert_eq!(
+            calls[1].2.as_ref().unwrap()["networkPeering"]["network"],
+            "https://www.googleapis.com/compute/v1/projects/shop-prod/global/networks/shop"
+        );
+
+        let half = |state: &str| Some(json!({ "peerings": [{ "name": "shop-warehouse", "state": state, "stateDetails": "" }] }));
+        api.respond(HttpMethod::Get, "/compute/v1/projects/shop-prod/global/networks/shop", half("ACTIVE"));
+        api.respond(HttpMethod::Get, "/compute/v1/projects/data-prod/global/networks/warehouse", half("INACTIVE"));
+        assert_eq!(gcp.peering_state("shop-warehouse", &local, &remote).await.unwrap(), PeeringState::Pending);
+        api.respond(HttpMethod::Get, "/compute/v1/projects/data-prod/global/networks/warehouse", half("ACTIVE"));
+        assert_eq!(gcp.peering_state("shop-warehouse", &local, &remote).await.unwrap(), PeeringState::Active);
+    }
+}
@@ -0,0 +1,868 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+use crate::error::{NetworkError, NetworkResult};
+use crate::loadbalancer::rules::Cidr;
+use crate::vpn::{
+    ConnectionStatus, CustomerGateway, RoutingConfiguration, StaticRoute, VpnConnection, VpnGateway, VpnManager, VpnType,
+};
+
+pub mod aws;
+pub mod azure;
+pub mod gcp;
+
+pub use aws::AwsPeering;
+pub use azure::AzurePeering;
+pub use gcp::GcpPeering;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NetworkProvider {
+    Aws,
+    Gcp,
+    Azure,
+}
+
+impl NetworkProvider {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NetworkProvider::Aws => "aws",
+            NetworkProvider::Gcp => "gcp",
+            NetworkProvider::Azure => "azure",
+        }
+    }
+}
+
+// The gateway a network reaches other clouds through, when peering has to go over VPN
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VpnAttachment {
+    // Next hop for routes towards the other side: a virtual private gateway, a GCP VPN tunnel
+    // or an Azure virtual network gateway
+    pub gateway_id: String,
+    pub public_ip: String,
+    pub bgp_asn: Option<u32>,
+}
+
+// One side of a peering. `network_id` is a VPC id on AWS, a network name on GCP and a
+// virtual network resource id on Azure; `account` is the account, project or subscription.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NetworkEndpoint {
+    pub provider: NetworkProvider,
+    pub account: String,
+    pub region: String,
+    pub network_id: String,
+    pub cidrs: Vec<String>,
+    // Route tables to update and extra destinations reachable through the peer
+    pub routing: RoutingConfiguration,
+    pub vpn_gateway: Option<VpnAttachment>,
+    // Addresses inside this network that reachability probes from the other side target
+    pub probe_targets: Vec<String>,
+}
+
+impl NetworkEndpoint {
+    pub fn new(provider: NetworkProvider, account: impl Into<String>, region: impl Into<String>, network_id: impl Into<String>) -> Self {
+        Self {
+            provider,
+            account: account.into(),
+            region: region.into(),
+            network_id: network_id.into(),
+            cidrs: Vec::new(),
+            routing: RoutingConfiguration {
+                propagate_routes: false,
+                static_routes: Vec::new(),
+                bgp_config: None,
+                route_tables: Vec::new(),
+            },
+            vpn_gateway: None,
+            probe_targets: Vec::new(),
+        }
+    }
+
+    pub fn with_cidr(mut self, cidr: impl Into<String>) -> Self {
+        self.cidrs.push(cidr.into());
+        self
+    }
+
+    pub fn with_route_table(mut self, table: impl Into<String>) -> Self {
+        self.routing.route_tables.push(table.into());
+        self
+    }
+
+    pub fn with_vpn_gateway(mut self, gateway: VpnAttachment) -> Self {
+        self.vpn_gateway = Some(gateway);
+        self
+    }
+
+    pub fn with_probe_target(mut self, address: impl Into<String>) -> Self {
+        self.probe_targets.push(address.into());
+        self
+    }
+
+    pub fn label(&self) -> String {
+        format!("{} {}", self.provider.as_str(), self.network_id)
+    }
+
+    fn same_network(&self, other: &NetworkEndpoint) -> bool {
+        self.provider == other.provider && self.account == other.account && self.network_id == other.network_id
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PeeringSide {
+    Local,
+    Remote,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "id", rename_all = "snake_case")]
+pub enum NextHop {
+    Peering(String),
+    VpnGateway(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlannedRoute {
+    pub side: PeeringSide,
+    pub route_table: String,
+    pub destination_cidr: String,
+}
+
+// A route the manager added, and so the only kind it will ever remove
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManagedRoute {
+    pub side: PeeringSide,
+    pub route_table: String,
+    pub destination_cidr: String,
+    pub next_hop: NextHop,
+}
+
+// A route as a provider reports it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RouteEntry {
+    pub destination_cidr: String,
+    pub next_hop: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PlannedTransport {
+    Native { provider: NetworkProvider },
+    // Clouds cannot peer with each other, so the networks are joined by this connection
+    Vpn { connection: Box<VpnConnection> },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeeringPlan {
+    pub transport: PlannedTransport,
+    pub routes: Vec<PlannedRoute>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "id", rename_all = "snake_case")]
+pub enum PeeringTransport {
+    Native(String),
+    Vpn(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", content = "reason", rename_all = "snake_case")]
+pub enum PeeringState {
+    Pending,
+    Active,
+    Failed(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", content = "unreachable", rename_all = "snake_case")]
+pub enum PeeringHealth {
+    Unknown,
+    Healthy,
+    // Some probe targets answer and these do not
+    Degraded(Vec<String>),
+    Unreachable(Vec<String>),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Peering {
+    pub id: String,
+    pub name: String,
+    pub local: NetworkEndpoint,
+    pub remote: NetworkEndpoint,
+    pub transport: PeeringTransport,
+    pub state: PeeringState,
+    pub health: PeeringHealth,
+    pub routes: Vec<ManagedRoute>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Peering {
+    fn endpoint(&self, side: PeeringSide) -> &NetworkEndpoint {
+        match side {
+            PeeringSide::Local => &self.local,
+            PeeringSide::Remote => &self.remote,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HttpMethod {
+    Get,
+    Post,
+    Put,
+    Delete,
+}
+
+// Authenticated JSON calls against a provider's network API; request signing is the
+// transport's job
+#[async_trait]
+pub trait CloudApi: Send + Sync {
+    // `None` when the resource does not exist
+    async fn call(&self, method: HttpMethod, path: &str, body: Option<Value>) -> NetworkResult<Option<Value>>;
+}
+
+// Same-provider peering and route tables on one cloud
+#[async_trait]
+pub trait PeeringProvider: Send + Sync {
+    fn provider(&self) -> NetworkProvider;
+    // GCP and Azure peerings exchange subnet routes themselves; AWS needs route table entries
+    fn exchanges_routes(&self) -> bool;
+    async fn create_peering(&self, name: &str, local: &NetworkEndpoint, remote: &NetworkEndpoint) -> NetworkResult<String>;
+    async fn peering_state(&self, id: &str, local: &NetworkEndpoint, remote: &NetworkEndpoint) -> NetworkResult<PeeringState>;
+    async fn delete_peering(&self, id: &str, local: &NetworkEndpoint, remote: &NetworkEndpoint) -> NetworkResult<()>;
+    async fn list_routes(&self, endpoint: &NetworkEndpoint, route_table: &str) -> NetworkResult<Vec<RouteEntry>>;
+    async fn add_route(&self, endpoint: &NetworkEndpoint, route: &ManagedRoute) -> NetworkResult<()>;
+    async fn remove_route(&self, endpoint: &NetworkEndpoint, route: &ManagedRoute) -> NetworkResult<()>;
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProbeResult {
+    pub reachable: bool,
+    pub latency_ms: Option<f64>,
+}
+
+// Checks that an address in the other network answers from inside `from`
+#[async_trait]
+pub trait ReachabilityProbe: Send + Sync {
+    async fn probe(&self, from: &NetworkEndpoint, target: &str) -> NetworkResult<ProbeResult>;
+}
+
+// Every pair of blocks that would make routing ambiguous, described for the error message
+pub fn overlapping_cidrs(local: &NetworkEndpoint, remote: &NetworkEndpoint) -> NetworkResult<Vec<String>> {
+    let blocks = |endpoint: &NetworkEndpoint| -> NetworkResult<Vec<(String, Cidr)>> {
+        endpoint.cidrs.iter().map(|c| Ok((c.clone(), Cidr::parse(c)?))).collect()
+    };
+    let (ours, theirs) = (blocks(local)?, blocks(remote)?);
+    let mut overlaps = Vec::new();
+    for (a, a_block) in &ours {
+        for (b, b_block) in &theirs {
+            if a_block.overlaps(b_block) {
+                overlaps.push(format!("{} ({}) overlaps {} ({})", a, local.label(), b, remote.label()));
+            }
+        }
+    }
+    Ok(overlaps)
+}
+
+// Creates peerings between networks we manage, natively when both sides are on one cloud
+// and over a VPN connection otherwise, and owns the routes that make them usable
+pub struct PeeringManager {
+    providers: HashMap<NetworkProvider, Arc<dyn PeeringProvider>>,
+    vpn: Option<Arc<dyn VpnManager>>,
+    probe: Option<Arc<dyn ReachabilityProbe>>,
+    peerings: RwLock<HashMap<String, Peering>>,
+}
+
+impl Default for PeeringManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PeeringManager {
+    pub fn new() -> Self {
+        Self { providers: HashMap::new(), vpn: None, probe: None, peerings: RwLock::new(HashMap::new()) }
+    }
+
+    pub fn with_provider(mut self, provider: Arc<dyn PeeringProvider>) -> Self {
+        self.providers.insert(provider.provider(), provider);
+        self
+    }
+
+    pub fn with_vpn(mut self, vpn: Arc<dyn VpnManager>) -> Self {
+        self.vpn = Some(vpn);
+        self
+    }
+
+    pub fn with_probe(mut self, probe: Arc<dyn ReachabilityProbe>) -> Self {
+        self.probe = Some(probe);
+        self
+    }
+
+    fn provider(&self, provider: NetworkProvider) -> NetworkResult<&Arc<dyn PeeringProvider>> {
+        self.providers
+            .get(&provider)
+            .ok_or_else(|| NetworkError::Config(format!("No peering provider configured for {}", provider.as_str())))
+    }
+
+    pub async fn plan_peering(&self, name: &str, local: &NetworkEndpoint, remote: &NetworkEndpoint) -> NetworkResult<PeeringPlan> {
+        if local.same_network(remote) {
+            return Err(NetworkError::Validation(format!("{} cannot be peered with itself", local.label())));
+        }
+        if local.cidrs.is_empty() || remote.cidrs.is_empty() {
+            return Err(NetworkError::Validation("Both networks need at least one CIDR block".into()));
+        }
+        let overlaps = overlapping_cidrs(local, remote)?;
+        if !overlaps.is_empty() {
+            return Err(NetworkError::Validation(format!("Overlapping address space: {}", overlaps.join("; "))));
+        }
+        // Networks already peered with either side would end up with ambiguous routes too
+        for existing in self.peerings.read().await.values() {
+            for (endpoint, other) in [(local, remote), (remote, local)] {
+                let neighbour = if existing.local.same_network(endpoint) {
+                    &existing.remote
+                } else if existing.remote.same_network(endpoint) {
+                    &existing.local
+                } else {
+                    continue;
+                };
+                if neighbour.same_network(other) {
+                    return Err(NetworkError::Conflict(format!(
+                        "{} and {} are already peered as {}",
+                        local.label(),
+                        remote.label(),
+                        existing.id
+                    )));
+                }
+                let overlaps = overlapping_cidrs(neighbour, other)?;
+                if !overlaps.is_empty() {
+                    return Err(NetworkError::Validation(format!(
+                        "{} is already peered with {} ({}): {}",
+                        endpoint.label(),
+                        neighbour.label(),
+                        existing.id,
+                        overlaps.join("; ")
+                    )));
+                }
+            }
+        }
+
+        let native = local.provider == remote.provider;
+        let transport = if native {
+            self.provider(local.provider)?;
+            PlannedTransport::Native { provider: local.provider }
+        } else {
+            PlannedTransport::Vpn { connection: Box::new(vpn_connection(name, local, remote)?) }
+        };
+        let mut routes = Vec::new();
+        for (side, endpoint, other) in [(PeeringSide::Local, local, remote), (PeeringSide::Remote, remote, local)] {
+            let provider = self.provider(endpoint.provider)?;
+            if (native && provider.exchanges_routes()) || (!native && endpoint.routing.propagate_routes) {
+                continue;
+            }
+            let mut destinations: Vec<String> = other.cidrs.clone();
+            for route in &endpoint.routing.static_routes {
+                Cidr::parse(&route.destination_cidr)?;
+                destinations.push(route.destination_cidr.clone());
+            }
+            for table in &endpoint.routing.route_tables {
+                for destination in &destinations {
+                    routes.push(PlannedRoute {
+                        side,
+                        route_table: table.clone(),
+                        destination_cidr: destination.clone(),
+                    });
+                }
+            }
+        }
+        Ok(PeeringPlan { transport, routes })
+    }
+
+    pub async fn create_peering(&self, name: &str, local: NetworkEndpoint, remote: NetworkEndpoint) -> NetworkResult<Peering> {
+        let plan = self.plan_peering(name, &local, &remote).await?;
+        let transport = match &plan.transport {
+            PlannedTransport::Native { provider } => {
+                let id = self.provider(*provider)?.create_peering(name, &local, &remote).await?;
+                PeeringTransport::Native(id)
+            }
+            PlannedTransport::Vpn { connection } => {
+                let vpn = self.vpn.as_ref().ok_or_else(|| {
+                    NetworkError::Config(format!(
+                        "Peering {} with {} needs a VPN connection, and no VPN manager is configured",
+                        local.label(),
+                        remote.label()
+                    ))
+                })?;
+                let connection = vpn.create_connection(connection.as_ref().clone()).await?;
+                PeeringTransport::Vpn(connection.id)
+            }
+        };
+        let now = Utc::now();
+        let mut peering = Peering {
+            id: uuid::Uuid::new_v4().to_string(),
+            name: name.to_string(),
+            local,
+            remote,
+            transport,
+            state: PeeringState::Pending,
+            health: PeeringHealth::Unknown,
+            routes: Vec::new(),
+            created_at: now,
+            updated_at: now,
+        };
+        if let Err(e) = self.add_routes(&mut peering, &plan.routes).await {
+            warn!(peering = %peering.name, "Rolling back peering: {}", e);
+            self.teardown(&mut peering).await;
+            return Err(e);
+        }
+        info!(peering = %peering.id, local = %peering.local.label(), remote = %peering.remote.label(), routes = peering.routes.len(), "Created peering");
+        self.peerings.write().await.insert(peering.id.clone(), peering.clone());
+        Ok(peering)
+    }
+
+    async fn add_routes(&self, peering: &mut Peering, planned: &[PlannedRoute]) -> NetworkResult<()> {
+        let mut tables: HashMap<(PeeringSide, String), Vec<RouteEntry>> = HashMap::new();
+        for route in planned {
+            let endpoint = peering.endpoint(route.side).clone();
+            let next_hop = match &peering.transport {
+                PeeringTransport::Native(id) => NextHop::Peering(id.clone()),
+                PeeringTransport::Vpn(_) => NextHop::VpnGateway(
+                    endpoint.vpn_gateway.as_ref().map(|g| g.gateway_id.clone()).unwrap_or_default(),
+                ),
+            };
+            let provider = self.provider(endpoint.provider)?;
+            let key = (route.side, route.route_table.clone());
+            if !tables.contains_key(&key) {
+                tables.insert(key.clone(), provider.list_routes(&endpoint, &route.route_table).await?);
+            }
+            let hop_id = match &next_hop {
+                NextHop::Peering(id) | NextHop::VpnGateway(id) => id,
+            };
+            // An identical route someone else made stays theirs; a different one is a clash
+            if let Some(existing) = tables[&key].iter().find(|r| r.destination_cidr == route.destination_cidr) {
+                if existing.next_hop.as_ref() == Some(hop_id) {
+                    continue;
+                }
+                return Err(NetworkError::Conflict(format!(
+                    "Route table {} on {} already routes {} via {}",
+                    route.route_table,
+                    endpoint.label(),
+                    route.destination_cidr,
+                    existing.next_hop.as_deref().unwrap_or("an unknown target")
+                )));
+            }
+            let managed = ManagedRoute {
+                side: route.side,
+                route_table: route.route_table.clone(),
+                destination_cidr: route.destination_cidr.clone(),
+                next_hop,
+            };
+            provider.add_route(&endpoint, &managed).await?;
+            peering.routes.push(managed);
+        }
+        Ok(())
+    }
+
+    // Best effort: removes what this peering added, then the peering itself
+    async fn teardown(&self, peering: &mut Peering) -> Vec<String> {
+        let mut failures = Vec::new();
+        let mut kept = Vec::new();
+        for route in std::mem::take(&mut peering.routes) {
+            let endpoint = peering.endpoint(route.side);
+            let result = match self.provider(endpoint.provider) {
+                Ok(provider) => provider.remove_route(endpoint, &route).await,
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(()) | Err(NetworkError::NotFound(_)) => {}
+                Err(e) => {
+                    failures.push(format!("route {} in {}: {}", route.destination_cidr, route.route_table, e));
+                    kept.push(route);
+                }
+            }
+        }
+        peering.routes = kept;
+        if !failures.is_empty() {
+            return failures;
+        }
+        let result = match &peering.transport {
+            PeeringTransport::Native(id) => match self.provider(peering.local.provider) {
+                Ok(provider) => provider.delete_peering(id, &peering.local, &peering.remote).await,
+                Err(e) => Err(e),
+            },
+            PeeringTransport::Vpn(id) => match &self.vpn {
+                Some(vpn) => vpn.delete_connection(id).await,
+                None => Err(NetworkError::Config("No VPN manager is configured".into())),
+            },
+        };
+        match result {
+            Ok(()) | Err(NetworkError::NotFound(_)) => {}
+            Err(e) => failures.push(format!("peering: {}", e)),
+        }
+        failures
+    }
+
+    // Removes the routes this peering created, and only those, before the peering itself.
+    // Anything that could not be removed keeps the record so the delete can be retried.
+    pub async fn delete_peering(&self, id: &str) -> NetworkResult<()> {
+        let mut peering = self.get_peering(id).await?;
+        let failures = self.teardown(&mut peering).await;
+        if !failures.is_empty() {
+            peering.updated_at = Utc::now();
+            self.peerings.write().await.insert(peering.id.clone(), peering);
+            return Err(NetworkError::Provider(format!("Failed to delete peering {}: {}", id, failures.join("; "))));
+        }
+        self.peerings.write().await.remove(id);
+        info!(peering = id, "Deleted peering");
+        Ok(())
+    }
+
+    pub async fn get_peering(&self, id: &str) -> NetworkResult<Peering> {
+        self.peerings
+            .read()
+            .await
+            .get(id)
+            .cloned()
+            .ok_or_else(|| NetworkError::NotFound(format!("Peering {} not found", id)))
+    }
+
+    pub async fn list_peerings(&self) -> Vec<Peering> {
+        let mut peerings: Vec<Peering> = self.peerings.read().await.values().cloned().collect();
+        peerings.sort_by_key(|p| p.created_at);
+        peerings
+    }
+
+    // Refreshes the provider's view of the link, then probes both directions once it is up
+    pub async fn refresh_status(&self, id: &str) -> NetworkResult<Peering> {
+        let mut peering = self.get_peering(id).await?;
+        peering.state = match &peering.transport {
+            PeeringTransport::Native(provider_id) => {
+                self.provider(peering.local.provider)?.peering_state(provider_id, &peering.local, &peering.remote).await?
+            }
+            PeeringTransport::Vpn(connection_id) => {
+                let vpn = self.vpn.as_ref().ok_or_else(|| NetworkError::Config("No VPN manager is configured".into()))?;
+                match vpn.get_connection(connection_id).await?.status {
+                    ConnectionStatus::Available => PeeringState::Active,
+                    ConnectionStatus::Failed => PeeringState::Failed(format!("VPN connection {} failed", connection_id)),
+                    _ => PeeringState::Pending,
+                }
+            }
+        };
+        if let (PeeringState::Active, Some(probe)) = (&peering.state, &self.probe) {
+            let mut reachable = 0;
+            let mut unreachable = Vec::new();
+            for (from, to) in [(&peering.local, &peering.remote), (&peering.remote, &peering.local)] {
+                for target in &to.probe_targets {
+                    match probe.probe(from, target).await {
+                        Ok(result) if result.reachable => reachable += 1,
+                        Ok(_) => unreachable.push(format!("{} from {}", target, from.label())),
+                        Err(e) => unreachable.push(format!("{} from {} ({})", target, from.label(), e)),
+                    }
+                }
+            }
+            peering.health = match (reachable, unreachable.is_empty()) {
+                (0, true) => PeeringHealth::Unknown,
+                (_, true) => PeeringHealth::Healthy,
+                (0, false) => PeeringHealth::Unreachable(unreachable),
+                (_, false) => PeeringHealth::Degraded(unreachable),
+            };
+        } else if peering.state != PeeringState::Active {
+            peering.health = PeeringHealth::Unknown;
+        }
+        peering.updated_at = Utc::now();
+        self.peerings.write().await.insert(peering.id.clone(), peering.clone());
+        Ok(peering)
+    }
+}
+
+// The connection that joins two clouds: `local` plays the VPN gateway and `remote` the
+// customer gateway
+fn vpn_connection(name: &str, local: &NetworkEndpoint, remote: &NetworkEndpoint) -> NetworkResult<VpnConnection> {
+    let gateway = |endpoint: &NetworkEndpoint| {
+        endpoint.vpn_gateway.clone().ok_or_else(|| {
+            NetworkError::Validation(format!(
+                "{} and {} are on different clouds, so {} needs a VPN gateway",
+                local.label(),
+                remote.label(),
+                endpoint.label()
+            ))
+        })
+    };
+    let (ours, theirs) = (gateway(local)?, gateway(remote)?);
+    let connection_type = match (&local.routing.bgp_config, theirs.bgp_asn) {
+        (Some(_), Some(asn)) => VpnType::DynamicRouting { bgp_asn: asn },
+        _ => VpnType::StaticRouting,
+    };
+    let now = Utc::now();
+    Ok(VpnConnection {
+        id: format!("peering-{}", uuid::Uuid::new_v4().simple()),
+        name: name.to_string(),
+        connection_type,
+        status: ConnectionStatus::Pending,
+        customer_gateway: CustomerGateway {
+            id: theirs.gateway_id.clone(),
+            ip_address: theirs.public_ip.clone(),
+            bgp_asn: theirs.bgp_asn,
+            device: None,
+            certificate: None,
+        },
+        vpn_gateway: VpnGateway {
+            id: ours.gateway_id.clone(),
+            vpc_id: local.network_id.clone(),
+            availability_zone: local.region.clone(),
+            public_ip: ours.public_ip.clone(),
+            private_ip: String::new(),
+        },
+        routing: RoutingConfiguration {
+            propagate_routes: local.routing.propagate_routes,
+            static_routes: remote
+                .cidrs
+                .iter()
+                .map(|cidr| StaticRoute { destination_cidr: cidr.clone(), next_hop: theirs.public_ip.clone() })
+                .collect(),
+            bgp_config: local.routing.bgp_config.clone(),
+            route_tables: local.routing.route_tables.clone(),
+        },
+        tunnels: Vec::new(),
+        tags: HashMap::from([("sirsi:peering".to_string(), name.to_string())]),
+        created_at: now,
+        updated_at: now,
+    })
+}
+
+// Shared by the provider implementations
+pub(crate) fn text(value: &Value, path: &[&str]) -> Option<String> {
+    path.iter().try_fold(value, |v, key| v.get(key))?.as_str().map(str::to_string)
+}
+
+pub(crate) fn missing(what: &str) -> NetworkError {
+    NetworkError::Provider(format!("Provider response is missing {}", what))
+}
+
+// A stable, provider-safe name for a route we own: `sirsi-<peering>-<cidr>`
+pub(crate) fn route_name(next_hop: &NextHop, destination: &str) -> String {
+    let (NextHop::Peering(id) | NextHop::VpnGateway(id)) = next_hop;
+    let hop: String = id.chars().filter(|c| c.is_ascii_alphanumeric()).take(12).collect::<String>().to_lowercase();
+    let cidr: String = destination.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '-' }).collect();
+    format!("sirsi-{}-{}", hop, cidr)
+}
+
+#[cfg(test)]
+pub(crate) mod testing {
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    use super::*;
+
+    // Answers calls from a script keyed by `"{method:?} {path}"`, recording every call made
+    #[derive(Default)]
+    pub struct ScriptedApi {
+        pub responses: Mutex<HashMap<String, Option<Value>>>,
+        pub calls: Mutex<Vec<(HttpMethod, String, Option<Value>)>>,
+    }
+
+    impl ScriptedApi {
+        pub fn respond(&self, method: HttpMethod, path: &str, response: Option<Value>) {
+            self.responses.lock().unwrap().insert(format!("{:?} {}", method, path), response);
+        }
+    }
+
+    #[async_trait]
+    impl CloudApi for ScriptedApi {
+        async fn call(&self, method: HttpMethod, path: &str, body: Option<Value>) -> NetworkResult<Option<Value>> {
+            self.calls.lock().unwrap().push((method, path.to_string(), body));
+            Ok(self
+                .responses
+                .lock()
+                .unwrap()
+                .get(&format!("{:?} {}", method, path))
+                .cloned()
+                .unwrap_or(Some(Value::Null)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    // Route tables keyed by `(network, table)`
+    struct FakeProvider {
+        provider: NetworkProvider,
+        exchanges_routes: bool,
+        tables: Mutex<HashMap<(String, String), Vec<RouteEntry>>>,
+        peerings: Mutex<Vec<String>>,
+        fail_route: Option<String>,
+    }
+
+    impl FakeProvider {
+        fn new(provider: NetworkProvider) -> Self {
+            Self {
+                provider,
+                exchanges_routes: false,
+                tables: Mutex::new(HashMap::new()),
+                peerings: Mutex::new(Vec::new()),
+                fail_route: None,
+            }
+        }
+
+        fn routes(&self, network: &str, table: &str) -> Vec<RouteEntry> {
+            self.tables.lock().unwrap().get(&(network.to_string(), table.to_string())).cloned().unwrap_or_default()
+        }
+    }
+
+    #[async_trait]
+    impl PeeringProvider for FakeProvider {
+        fn provider(&self) -> NetworkProvider {
+            self.provider
+        }
+
+        fn exchanges_routes(&self) -> bool {
+            self.exchanges_routes
+        }
+
+        async fn create_peering(&self, _name: &str, _local: &NetworkEndpoint, _remote: &NetworkEndpoint) -> NetworkResult<String> {
+            let id = format!("pcx-{}", self.peerings.lock().unwrap().len() + 1);
+            self.peerings.lock().unwrap().push(id.clone());
+            Ok(id)
+        }
+
+        async fn peering_state(&self, _id: &str, _local: &NetworkEndpoint, _remote: &NetworkEndpoint) -> NetworkResult<PeeringState> {
+            Ok(PeeringState::Active)
+        }
+
+        async fn delete_peering(&self, id: &str, _local: &NetworkEndpoint, _remote: &NetworkEndpoint) -> NetworkResult<()> {
+            self.peerings.lock().unwrap().retain(|p| p != id);
+            Ok(())
+        }
+
+        async fn list_routes(&self, endpoint: &NetworkEndpoint, route_table: &str) -> NetworkResult<Vec<RouteEntry>> {
+            Ok(self.routes(&endpoint.network_id, route_table))
+        }
+
+        async fn add_route(&self, endpoint: &NetworkEndpoint, route: &ManagedRoute) -> NetworkResult<()> {
+            if self.fail_route.as_deref() == Some(route.destination_cidr.as_str()) {
+                return Err(NetworkError::Provider("RouteLimitExceeded".into()));
+            }
+            let (NextHop::Peering(hop) | NextHop::VpnGateway(hop)) = &route.next_hop;
+            self.tables
+                .lock()
+                .unwrap()
+                .entry((endpoint.network_id.clone(), route.route_table.clone()))
+                .or_default()
+                .push(RouteEntry { destination_cidr: route.destination_cidr.clone(), next_hop: Some(hop.clone()) });
+            Ok(())
+        }
+
+        async fn remove_route(&self, endpoint: &NetworkEndpoint, route: &ManagedRoute) -> NetworkResult<()> {
+            if let Some(routes) = self.tables.lock().unwrap().get_mut(&(endpoint.network_id.clone(), route.route_table.clone())) {
+                routes.retain(|r| r.destination_cidr != route.destination_cidr);
+            }
+            Ok(())
+        }
+    }
+
+    fn vpc(id: &str, cidr: &str) -> NetworkEndpoint {
+        NetworkEndpoint::new(NetworkProvider::Aws, "111122223333", "us-east-1", id).with_cidr(cidr).with_route_table(format!("rtb-{}", id))
+    }
+
+    #[tokio::test]
+    async fn test_overlapping_cidrs_are_refused_with_details() {
+        let manager = PeeringManager::new().with_provider(Arc::new(FakeProvider::new(NetworkProvider::Aws)));
+        let local = vpc("vpc-a", "10.0.0.0/16").with_cidr("10.1.0.0/16");
+        let remote = vpc("vpc-b", "10.1.128.0/17");
+        let err = manager.create_peering("a-b", local.clone(), remote).await.unwrap_err();
+        assert!(matches!(err, NetworkError::Validation(_)));
+        assert!(err.to_string().contains("10.1.0.0/16 (aws vpc-a) overlaps 10.1.128.0/17 (aws vpc-b)"));
+        assert!(!err.to_string().contains("10.0.0.0/16 (aws vpc-a) overlaps"));
+
+        // A third network clashing with one already peered to the same VPC is refused too
+        manager.create_peering("a-c", local.clone(), vpc("vpc-c", "172.16.0.0/16")).await.unwrap();
+        let err = manager.create_peering("a-d", local, vpc("vpc-d", "172.16.4.0/24")).await.unwrap_err();
+        assert!(err.to_string().contains("already peered with aws vpc-c"));
+    }
+
+    #[tokio::test]
+    async fn test_routes_are_tracked_and_only_ours_removed() {
+        let provider = Arc::new(FakeProvider::new(NetworkProvider::Aws));
+        // An existing identical route belongs to someone else and must survive deletion
+        provider.tables.lock().unwrap().insert(
+            ("vpc-b".into(), "rtb-vpc-b".into()),
+            vec![
+                RouteEntry { destination_cidr: "0.0.0.0/0".into(), next_hop: Some("igw-1".into()) },
+                RouteEntry { destination_cidr: "10.0.0.0/16".into(), next_hop: Some("pcx-1".into()) },
+            ],
+        );
+        let manager = PeeringManager::new().with_provider(provider.clone());
+        let mut local = vpc("vpc-a", "10.0.0.0/16").with_route_table("rtb-vpc-a-private");
+        local.routing.static_routes.push(StaticRoute { destination_cidr: "192.168.0.0/24".into(), next_hop: String::new() });
+        let peering = manager.create_peering("a-b", local, vpc("vpc-b", "10.2.0.0/16")).await.unwrap();
+
+        let added: Vec<(&str, &str)> = peering.routes.iter().map(|r| (r.route_table.as_str(), r.destination_cidr.as_str())).collect();
+        assert_eq!(
+            added,
+            vec![
+                ("rtb-vpc-a", "10.2.0.0/16"),
+                ("rtb-vpc-a", "192.168.0.0/24"),
+                ("rtb-vpc-a-private", "10.2.0.0/16"),
+                ("rtb-vpc-a-private", "192.168.0.0/24"),
+            ]
+        );
+        assert!(peering.routes.iter().all(|r| r.next_hop == NextHop::Peering("pcx-1".into())));
+        assert_eq!(provider.routes("vpc-b", "rtb-vpc-b").len(), 2);
+
+        manager.delete_peering(&peering.id).await.unwrap();
+        assert!(provider.routes("vpc-a", "rtb-vpc-a").is_empty());
+        assert!(provider.routes("vpc-a", "rtb-vpc-a-private").is_empty());
+        assert_eq!(provider.routes("vpc-b", "rtb-vpc-b").len(), 2);
+        assert!(provider.peerings.lock().unwrap().is_empty());
+        assert!(manager.get_peering(&peering.id).await.is_err());
+
+        // A route that cannot be added rolls back everything added before it
+        let mut failing = FakeProvider::new(NetworkProvider::Aws);
+        failing.fail_route = Some("10.0.0.0/16".into());
+        let failing = Arc::new(failing);
+        let manager = PeeringManager::new().with_provider(failing.clone());
+        assert!(manager.create_peering("a-b", vpc("vpc-a", "10.0.0.0/16"), vpc("vpc-b", "10.2.0.0/16")).await.is_err());
+        assert!(failing.routes("vpc-a", "rtb-vpc-a").is_empty());
+        assert!(failing.peerings.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_cross_provider_peering_plans_a_vpn() {
+        let mut gcp = FakeProvider::new(NetworkProvider::Gcp);
+        gcp.exchanges_routes = true;
+        let manager = PeeringManager::new()
+            .with_provider(Arc::new(FakeProvider::new(NetworkProvider::Aws)))
+            .with_provider(Arc::new(gcp));
+        let local = vpc("vpc-a", "10.0.0.0/16");
+        let remote = NetworkEndpoint::new(NetworkProvider::Gcp, "shop-prod", "us-east1", "shop-net")
+            .with_cidr("10.8.0.0/16")
+            .with_route_table("shop-net");
+        assert!(manager.plan_peering("hybrid", &local, &remote).await.is_err());
+
+        let local = local.with_vpn_gateway(VpnAttachment { gateway_id: "vgw-1".into(), public_ip: "203.0.113.1".into(), bgp_asn: None });
+        let remote = remote.with_vpn_gateway(VpnAttachment {
+            gateway_id: "projects/shop-prod/regions/us-east1/vpnTunnels/aws".into(),
+            public_ip: "198.51.100.7".into(),
+            bgp_asn: None,
+        });
+        let plan = manager.plan_peering("hybrid", &local, &remote).await.unwrap();
+        let PlannedTransport::Vpn { connection } = &plan.transport else { panic!("expected a VPN plan") };
+        assert_eq!(connection.customer_gateway.ip_address, "198.51.100.7");
+        assert_eq!(connection.routing.static_routes[0].destination_cidr, "10.8.0.0/16");
+        // GCP exchanges routes for native peerings, but a VPN needs explicit routes on both sides
+        assert_eq!(plan.routes.len(), 2);
+        assert!(manager.create_peering("hybrid", local, remote).await.unwrap_err().to_string().contains("no VPN manager"));
+    }
+}
@@ -5,6 +5,10 @@ use chrono::{DateTime, Utc};
 
 use crate::error::NetworkResult;
 
+pub mod recommend;
+
+pub use recommend::{apply_recommendations, dry_run_recommendations, ApplyPlan, FlowAnalyzer, RecommendationReport};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NetworkPolicy {
     pub id: String,
@@ -82,7 +86,7 @@ pub struct PortRule {
     pub end_port: Option<u16>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Protocol {
     TCP,
     UDP,
@@ -139,7 +143,7 @@ pub struct SecurityGroup {
     pub tags: HashMap<String, String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SecurityGroupRule {
     pub id: String,
     pub description: Option<String>,
@@ -182,7 +186,7 @@ pub struct ACLRule {
     pub icmp_code: Option<i32>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum RuleAction {
     Allow,
     Deny,
@@ -0,0 +1,708 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::net::IpAddr;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use super::{FlowLogRecord, NetworkACL, Protocol, RuleAction, SecurityGroup, SecurityGroupManager, SecurityGroupRule};
+use crate::error::{NetworkError, NetworkResult};
+use crate::ipam::Block;
+use crate::loadbalancer::rules::Cidr;
+
+const FULL_CONFIDENCE_AFTER_DAYS: i64 = 14;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Ingress,
+    Egress,
+}
+
+impl Direction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Direction::Ingress => "ingress",
+            Direction::Egress => "egress",
+        }
+    }
+}
+
+// Accepted traffic between one source and destination on one port, over the window
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ObservedFlow {
+    pub direction: Direction,
+    pub source: IpAddr,
+    pub destination: IpAddr,
+    pub port: u16,
+    pub protocol: Protocol,
+    pub records: u64,
+    pub packets: i64,
+    pub bytes: i64,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+}
+
+impl ObservedFlow {
+    // The address on the far side of the group
+    pub fn peer(&self) -> IpAddr {
+        match self.direction {
+            Direction::Ingress => self.source,
+            Direction::Egress => self.destination,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UnusedRule {
+    pub direction: Direction,
+    pub rule: SecurityGroupRule,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UnusedAclRule {
+    pub acl_id: String,
+    pub direction: Direction,
+    pub rule_number: i32,
+    pub action: RuleAction,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecommendationReport {
+    pub group_id: String,
+    pub window_start: DateTime<Utc>,
+    pub window_end: DateTime<Utc>,
+    // From the first record in the window to its end
+    pub observed_seconds: i64,
+    // 0-1; a short observation can miss weekly or monthly traffic
+    pub confidence: f64,
+    pub records_analyzed: usize,
+    pub flows: Vec<ObservedFlow>,
+    // The smallest rule set that still allows every observed flow
+    pub ingress: Vec<SecurityGroupRule>,
+    pub egress: Vec<SecurityGroupRule>,
+    // Existing rules no flow matched: unused, candidates for removal
+    pub unused_rules: Vec<UnusedRule>,
+    pub unused_acl_rules: Vec<UnusedAclRule>,
+}
+
+// The group as it is and as it would be after applying a report
+#[derive(Debug, Clone)]
+pub struct ApplyPlan {
+    pub before: SecurityGroup,
+    pub after: SecurityGroup,
+    pub added: Vec<(Direction, SecurityGroupRule)>,
+    pub removed: Vec<(Direction, SecurityGroupRule)>,
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ApplyResult {
+    pub plan: ApplyPlan,
+    // None when the plan was declined or changed nothing
+    pub applied: Option<SecurityGroup>,
+}
+
+// One parsed flow log record
+#[derive(Debug, Clone, Copy)]
+struct Flow<'a> {
+    source: IpAddr,
+    source_port: u16,
+    destination: IpAddr,
+    destination_port: u16,
+    protocol: Protocol,
+    accepted: bool,
+    record: &'a FlowLogRecord,
+}
+
+type FlowKey = (IpAddr, u16, IpAddr, u16, Protocol);
+// Hosts and groups seen on one protocol and port
+type PortPeers = (Protocol, Vec<Block>, BTreeSet<String>);
+// Protocol number, CIDRs and groups
+type PeerSet = (u8, Vec<String>, Vec<String>);
+
+impl Flow<'_> {
+    fn parse(record: &FlowLogRecord) -> Option<Flow<'_>> {
+        // NODATA and SKIPDATA records carry "-" for every field
+        Some(Flow {
+            source: record.source_address.parse().ok()?,
+            source_port: u16::try_from(record.source_port).ok()?,
+            destination: record.destination_address.parse().ok()?,
+            destination_port: u16::try_from(record.destination_port).ok()?,
+            protocol: protocol_from_number(record.protocol)?,
+            accepted: record.action.eq_ignore_ascii_case("ACCEPT"),
+            record,
+        })
+    }
+
+    fn key(&self) -> FlowKey {
+        (self.source, self.source_port, self.destination, self.destination_port, self.protocol)
+    }
+
+    fn reverse_key(&self) -> FlowKey {
+        (self.destination, self.destination_port, self.source, self.source_port, self.protocol)
+    }
+}
+
+fn protocol_from_number(number: i32) -> Option<Protocol> {
+    match number {
+        1 => Some(Protocol::ICMP),
+        6 => Some(Protocol::TCP),
+        17 => Some(Protocol::UDP),
+        132 => Some(Protocol::SCTP),
+        _ => None,
+    }
+}
+
+fn protocol_number(protocol: Protocol) -> u8 {
+    match protocol {
+        Protocol::ICMP => 1,
+        Protocol::TCP => 6,
+        Protocol::UDP => 17,
+        Protocol::SCTP => 132,
+    }
+}
+
+// Ports are ignored for ICMP, and a missing or negative start means all ports
+fn port_matches(protocol: Protocol, from_port: Option<i32>, to_port: Option<i32>, port: u16) -> bool {
+    match from_port {
+        _ if protocol == Protocol::ICMP => true,
+        None => true,
+        Some(from) if from < 0 => true,
+        Some(from) => (from..=to_port.unwrap_or(from)).contains(&(port as i32)),
+    }
+}
+
+fn cidr_contains(cidr: &str, address: IpAddr) -> bool {
+    match Cidr::parse(cidr) {
+        Ok(cidr) => cidr.contains(address),
+        Err(e) => {
+            warn!("Ignoring unparseable rule CIDR {}: {}", cidr, e);
+            false
+        }
+    }
+}
+
+// Fewest blocks covering exactly the given ones: sibling pairs fold into their parent, and
+// blocks inside an earlier one are dropped
+fn aggregate(mut blocks: Vec<Block>) -> Vec<Block> {
+    blocks.sort_by_key(|block| (block.v6, block.start, block.prefix));
+    blocks.dedup();
+    let mut merged: Vec<Block> = Vec::with_capacity(blocks.len());
+    for block in blocks {
+        if merged.last().is_some_and(|last| last.contains(&block)) {
+            continue;
+        }
+        merged.push(block);
+        while let [.., left, right] = merged[..] {
+            if left.v6 != right.v6 || left.prefix != right.prefix || left.prefix == 0 {
+                break;
+            }
+            let parent = Block { v6: left.v6, start: left.start, prefix: left.prefix - 1 };
+            if left.start & parent.host_mask() != 0 || right.start != left.end() + 1 {
+                break;
+            }
+            merged.truncate(merged.len() - 2);
+            merged.push(parent);
+        }
+    }
+    merged
+}
+
+// Contiguous runs of sorted ports
+fn port_ranges(ports: &[u16]) -> Vec<(u16, u16)> {
+    let mut ranges: Vec<(u16, u16)> = Vec::new();
+    for &port in ports {
+        match ranges.last_mut() {
+            Some((_, end)) if port as u32 == *end as u32 + 1 => *end = port,
+            _ => ranges.push((port, port)),
+        }
+    }
+    ranges
+}
+
+// What a rule allows, ignoring its id and description
+fn rule_shape(rule: &SecurityGroupRule) -> (u8, Option<i32>, Option<i32>, BTreeSet<&str>, BTreeSet<&str>) {
+    (
+        protocol_number(rule.protocol),
+        rule.from_port,
+        rule.to_port,
+        rule.cidr_blocks.iter().map(String::as_str).collect(),
+        rule.source_groups.iter().map(String::as_str).collect(),
+    )
+}
+
+pub struct FlowAnalyzer {
+    // Addresses of each security group's interfaces. Flows are attributed to a group by its
+    // members, and rules referencing another group match that group's members.
+    members: HashMap<String, HashSet<IpAddr>>,
+    full_confidence_after: Duration,
+}
+
+impl Default for FlowAnalyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FlowAnalyzer {
+    pub fn new() -> Self {
+        Self { members: HashMap::new(), full_confidence_after: Duration::days(FULL_CONFIDENCE_AFTER_DAYS) }
+    }
+
+    pub fn with_members(mut self, group_id: impl Into<String>, addresses: impl IntoIterator<Item = IpAddr>) -> Self {
+        self.members.entry(group_id.into()).or_default().extend(addresses);
+        self
+    }
+
+    pub fn with_full_confidence_after(mut self, after: Duration) -> Self {
+        self.full_confidence_after = after;
+        self
+    }
+
+    // `acls` are the network ACLs on the group members' subnets
+    pub fn analyze(
+        &self,
+        group: &SecurityGroup,
+        acls: &[NetworkACL],
+        records: &[FlowLogRecord],
+        window_start: DateTime<Utc>,
+        window_end: DateTime<Utc>,
+    ) -> NetworkResult<RecommendationReport> {
+        if window_end <= window_start {
+            return Err(NetworkError::Validation("Flow log window must end after it starts".into()));
+        }
+        let members = self
+            .members
+            .get(&group.id)
+            .filter(|members| !members.is_empty())
+            .ok_or_else(|| NetworkError::Validation(format!("No member addresses for security group {}", group.id)))?;
+        let direction = |flow: &Flow| {
+            if members.contains(&flow.destination) {
+                Some(Direction::Ingress)
+            } else if members.contains(&flow.source) {
+                Some(Direction::Egress)
+            } else {
+                None
+            }
+        };
+
+        let in_window: Vec<_> = records.iter().filter(|r| r.timestamp >= window_start && r.timestamp < window_end).collect();
+        let flows: Vec<(Direction, Flow)> =
+            in_window.iter().filter_map(|r| Flow::parse(r)).filter_map(|flow| Some((direction(&flow)?, flow))).collect();
+
+        // Security groups are stateful, so the reply half of a connection needs no rule of its
+        // own. A reply runs from the server's port back to the client's higher ephemeral one.
+        let accepted: HashSet<FlowKey> = flows.iter().filter(|(_, f)| f.accepted).map(|(_, f)| f.key()).collect();
+        let requests = flows
+            .iter()
+            .filter(|(_, f)| f.accepted)
+            .filter(|(_, f)| !(accepted.contains(&f.reverse_key()) && f.source_port < f.destination_port));
+
+        let mut observed: HashMap<(Direction, IpAddr, IpAddr, u16, Protocol), ObservedFlow> = HashMap::new();
+        for (direction, flow) in requests {
+            let port = if flow.protocol == Protocol::ICMP { 0 } else { flow.destination_port };
+            let entry = observed.entry((*direction, flow.source, flow.destination, port, flow.protocol)).or_insert_with(|| {
+                ObservedFlow {
+                    direction: *direction,
+                    source: flow.source,
+                    destination: flow.destination,
+                    port,
+                    protocol: flow.protocol,
+                    records: 0,
+                    packets: 0,
+                    bytes: 0,
+                    first_seen: flow.record.timestamp,
+                    last_seen: flow.record.timestamp,
+                }
+            });
+            entry.records += 1;
+            entry.packets += flow.record.packets;
+            entry.bytes += flow.record.bytes;
+            entry.first_seen = entry.first_seen.min(flow.record.timestamp);
+            entry.last_seen = entry.last_seen.max(flow.record.timestamp);
+        }
+        let mut observed: Vec<ObservedFlow> = observed.into_values().collect();
+        observed.sort_by_key(|f| (f.direction, f.source, f.destination, f.port, protocol_number(f.protocol)));
+
+        let mut unused_rules = Vec::new();
+        let mut recommended = HashMap::new();
+        for (direction, rules) in [(Direction::Ingress, &group.ingress_rules), (Direction::Egress, &group.egress_rules)] {
+            let flows: Vec<&ObservedFlow> = observed.iter().filter(|f| f.direction == direction).collect();
+            let mut matched = vec![false; rules.len()];
+            // Peers allowed through a group reference stay expressed as that group
+            let mut via_group: HashMap<(IpAddr, u16, Protocol), String> = HashMap::new();
+            for flow in &flows {
+                for (i, rule) in rules.iter().enumerate() {
+                    if rule.protocol != flow.protocol || !port_matches(rule.protocol, rule.from_port, rule.to_port, flow.port) {
+                        continue;
+                    }
+                    if rule.cidr_blocks.iter().any(|cidr| cidr_contains(cidr, flow.peer())) {
+                        matched[i] = true;
+                    }
+                    let group = rule.source_groups.iter().find(|g| self.members.get(*g).is_some_and(|m| m.contains(&flow.peer())));
+                    if let Some(group) = group {
+                        matched[i] = true;
+                        via_group.entry((flow.peer(), flow.port, flow.protocol)).or_insert_with(|| group.clone());
+                    }
+                }
+            }
+            unused_rules.extend(
+                rules.iter().zip(matched).filter(|(_, used)| !used).map(|(rule, _)| UnusedRule { direction, rule: rule.clone() }),
+            );
+            recommended.insert(direction, Self::cover(direction, &flows, &via_group));
+        }
+
+        let report = RecommendationReport {
+            group_id: group.id.clone(),
+            window_start,
+            window_end,
+            observed_seconds: 0,
+            confidence: 0.0,
+            records_analyzed: in_window.len(),
+            unused_acl_rules: Self::unused_acl_rules(acls, &flows),
+            flows: observed,
+            ingress: recommended.remove(&Direction::Ingress).unwrap_or_default(),
+            egress: recommended.remove(&Direction::Egress).unwrap_or_default(),
+            unused_rules,
+        };
+        let observed_seconds = in_window
+            .iter()
+            .map(|r| r.timestamp)
+            .min()
+            .map(|first| (window_end - first.max(window_start)).num_seconds())
+            .unwrap_or(0);
+        let confidence = (observed_seconds as f64 / self.full_confidence_after.num_seconds().max(1) as f64).min(1.0);
+        Ok(RecommendationReport { observed_seconds, confidence, ..report })
+    }
+
+    // One rule per protocol and set of peers, over each contiguous run of ports they share
+    fn cover(
+        direction: Direction,
+        flows: &[&ObservedFlow],
+        via_group: &HashMap<(IpAddr, u16, Protocol), String>,
+    ) -> Vec<SecurityGroupRule> {
+        let mut by_port: BTreeMap<(u8, u16), PortPeers> = BTreeMap::new();
+        for flow in flows {
+            let (_, hosts, groups) = by_port
+                .entry((protocol_number(flow.protocol), flow.port))
+                .or_insert_with(|| (flow.protocol, Vec::new(), BTreeSet::new()));
+            match via_group.get(&(flow.peer(), flow.port, flow.protocol)) {
+                Some(group) => {
+                    groups.insert(group.clone());
+                }
+                None => hosts.push(Block::host(flow.peer())),
+            }
+        }
+
+        let mut by_peers: BTreeMap<PeerSet, (Protocol, Vec<u16>)> = BTreeMap::new();
+        for ((number, port), (protocol, hosts, groups)) in by_port {
+            let cidrs = aggregate(hosts).iter().map(Block::to_string).collect();
+            by_peers.entry((number, cidrs, groups.into_iter().collect())).or_insert_with(|| (protocol, Vec::new())).1.push(port);
+        }
+
+        let mut rules = Vec::new();
+        for ((_, cidr_blocks, source_groups), (protocol, ports)) in by_peers {
+            for (from, to) in port_ranges(&ports) {
+                let (from_port, to_port) = match protocol {
+                    Protocol::ICMP => (None, None),
+                    _ => (Some(from as i32), Some(to as i32)),
+                };
+                rules.push(SecurityGroupRule {
+                    id: format!("recommended-{}-{}", direction.as_str(), rules.len() + 1),
+                    description: Some("Covers traffic observed in flow logs".into()),
+                    protocol,
+                    from_port,
+                    to_port,
+                    cidr_blocks: cidr_blocks.clone(),
+                    source_groups: source_groups.clone(),
+                });
+            }
+        }
+        rules
+    }
+
+    // ACLs are stateless and first-match, so every flow counts here, replies and rejected
+    // traffic included, against the lowest-numbered rule it matches
+    fn unused_acl_rules(acls: &[NetworkACL], flows: &[(Direction, Flow)]) -> Vec<UnusedAclRule> {
+        let mut unused = Vec::new();
+        for acl in acls {
+            for (direction, rules) in [(Direction::Ingress, &acl.inbound_rules), (Direction::Egress, &acl.outbound_rules)] {
+                let mut ordered: Vec<_> = rules.iter().collect();
+                ordered.sort_by_key(|rule| rule.rule_number);
+                let mut hit: HashSet<i32> = HashSet::new();
+                for (_, flow) in flows.iter().filter(|(d, _)| *d == direction) {
+                    let (peer, port) = match direction {
+                        Direction::Ingress => (flow.source, flow.destination_port),
+                        Direction::Egress => (flow.destination, flow.destination_port),
+                    };
+                    let first = ordered.iter().find(|rule| {
+                        rule.protocol == flow.protocol
+                            && port_matches(rule.protocol, rule.from_port, rule.to_port, port)
+                            && cidr_contains(&rule.cidr_block, peer)
+                    });
+                    if let Some(rule) = first {
+                        hit.insert(rule.rule_number);
+                    }
+                }
+                unused.extend(ordered.into_iter().filter(|rule| !hit.contains(&rule.rule_number)).map(|rule| UnusedAclRule {
+                    acl_id: acl.id.clone(),
+                    direction,
+                    rule_number: rule.rule_number,
+                    action: rule.rule_action,
+                }));
+            }
+        }
+        unused
+    }
+}
+
+// Rules already matching a recommendation keep their ids; the rest are swapped for the
+// recommended ones
+pub async fn dry_run_recommendations(
+    manager: &dyn SecurityGroupManager,
+    report: &RecommendationReport,
+) -> NetworkResult<ApplyPlan> {
+    let before = manager.get_security_group(&report.group_id).await?;
+    let mut after = before.clone();
+    let mut added = Vec::new();
+    let mut removed = Vec::new();
+    for (direction, current, recommended, rules) in [
+        (Direction::Ingress, &before.ingress_rules, &report.ingress, &mut after.ingress_rules),
+        (Direction::Egress, &before.egress_rules, &report.egress, &mut after.egress_rules),
+    ] {
+        let wanted: HashSet<_> = recommended.iter().map(rule_shape).collect();
+        let kept: Vec<SecurityGroupRule> = current.iter().filter(|rule| wanted.contains(&rule_shape(rule))).cloned().collect();
+        let have: HashSet<_> = kept.iter().map(rule_shape).collect();
+        removed.extend(current.iter().filter(|rule| !wanted.contains(&rule_shape(rule))).map(|rule| (direction, rule.clone())));
+        let new: Vec<SecurityGroupRule> = recommended.iter().filter(|rule| !have.contains(&rule_shape(rule))).cloned().collect();
+        added.extend(new.iter().map(|rule| (direction, rule.clone())));
+        *rules = kept.into_iter().chain(new).collect();
+    }
+
+    let mut warnings = Vec::new();
+    if report.confidence < 1.0 {
+        warnings.push(format!(
+            "Only {:.1} days of flow logs observed; traffic that did not occur in that time will be blocked",
+            report.observed_seconds as f64 / 86_400.0
+        ));
+    }
+    if report.flows.is_empty() {
+        warnings.push(format!("No traffic observed; every rule on {} would be removed", report.group_id));
+    }
+    Ok(ApplyPlan { before, after, added, removed, warnings })
+}
+
+// Always dry-runs first: `confirm` sees the plan against the group as it is now, and nothing
+// is written unless it returns true
+pub async fn apply_recommendations(
+    manager: &dyn SecurityGroupManager,
+    report: &RecommendationReport,
+    confirm: impl FnOnce(&ApplyPlan) -> bool,
+) -> NetworkResult<ApplyResult> {
+    let plan = dry_run_recommendations(manager, report).await?;
+    if (plan.added.is_empty() && plan.removed.is_empty()) || !confirm(&plan) {
+        return Ok(ApplyResult { plan, applied: None });
+    }
+    let applied = manager.update_security_group(plan.after.clone()).await?;
+    info!(
+        "Applied flow log recommendations to {}: {} rules added, {} removed",
+        report.group_id,
+        plan.added.len(),
+        plan.removed.len()
+    );
+    Ok(ApplyResult { plan, applied: Some(applied) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::ACLRule;
+    use async_trait::async_trait;
+    use chrono::TimeZone;
+    use std::sync::Mutex;
+
+    const LOGS: &str = "\
+2 111122223333 eni-0a1 10.0.0.5 10.0.1.10 40000 443 6 12 4800 1719792000 1719792060 ACCEPT OK
+2 111122223333 eni-0a2 10.0.0.6 10.0.1.11 40001 443 6 10 4100 1719878400 1719878460 ACCEPT OK
+2 111122223333 eni-0a1 10.1.0.4 10.0.1.10 50000 8080 6 8 2200 1719964800 1719964860 ACCEPT OK
+2 111122223333 eni-0a1 10.0.1.10 10.1.0.4 8080 50000 6 8 9100 1719964800 1719964860 ACCEPT OK
+2 111122223333 eni-0a1 10.1.0.5 10.0.1.10 50001 8080 6 5 1300 1720051200 1720051260 ACCEPT OK
+2 111122223333 eni-0a1 10.1.0.4 10.0.1.10 50002 8081 6 5 1300 1720137600 1720137660 ACCEPT OK
+2 111122223333 eni-0a1 10.1.0.5 10.0.1.10 50003 8081 6 5 1300 1720137600 1720137660 ACCEPT OK
+2 111122223333 eni-0a1 10.0.1.10 10.2.3.4 41000 5432 6 20 6000 1720224000 1720224060 ACCEPT OK
+2 111122223333 eni-0a1 203.0.113.9 10.0.1.10 1234 22 6 1 60 1720224000 1720224060 REJECT OK
+2 111122223333 eni-0b7 10.5.0.1 10.5.0.2 33000 80 6 3 180 1720224000 1720224060 ACCEPT OK
+2 111122223333 eni-0a1 - - - - - - - 1720224000 1720224060 - NODATA";
+
+    // Parses the default (version 2) flow log format
+    fn fixture_logs() -> Vec<FlowLogRecord> {
+        LOGS.lines()
+            .map(|line| {
+                let f: Vec<&str> = line.split_whitespace().collect();
+                let num = |i: usize| f[i].parse::<i64>().unwrap_or(-1);
+                FlowLogRecord {
+                    timestamp: Utc.timestamp_opt(num(10), 0).unwrap(),
+                    version: num(0) as i32,
+                    account_id: f[1].into(),
+                    interface_id: f[2].into(),
+                    source_address: f[3].into(),
+                    destination_address: f[4].into(),
+                    source_port: num(5) as i32,
+                    destination_port: num(6) as i32,
+                    protocol: num(7) as i32,
+                    packets: num(8),
+                    bytes: num(9),
+                    start_time: num(10),
+                    end_time: num(11),
+                    action: f[12].into(),
+                    log_status: f[13].into(),
+                }
+            })
+            .collect()
+    }
+
+    fn rule(id: &str, protocol: Protocol, ports: (i32, i32), cidrs: &[&str], groups: &[&str]) -> SecurityGroupRule {
+        SecurityGroupRule {
+            id: id.into(),
+            description: None,
+            protocol,
+            from_port: Some(ports.0),
+            to_port: Some(ports.1),
+            cidr_blocks: cidrs.iter().map(|c| c.to_string()).collect(),
+            source_groups: groups.iter().map(|g| g.to_string()).collect(),
+        }
+    }
+
+    fn web_group() -> SecurityGroup {
+        SecurityGroup {
+            id: "sg-web".into(),
+            name: "web".into(),
+            description: "Web tier".into(),
+            vpc_id: "vpc-1".into(),
+            ingress_rules: vec![
+                rule("sgr-any", Protocol::TCP, (0, 65535), &["0.0.0.0/0"], &[]),
+                rule("sgr-dns", Protocol::UDP, (53, 53), &["10.0.0.0/8"], &[]),
+                rule("sgr-lb", Protocol::TCP, (443, 443), &[], &["sg-lb"]),
+            ],
+            egress_rules: vec![rule("sgr-out", Protocol::TCP, (0, 65535), &["0.0.0.0/0"], &[])],
+            tags: HashMap::new(),
+        }
+    }
+
+    fn analyzer() -> FlowAnalyzer {
+        let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+        FlowAnalyzer::new()
+            .with_members("sg-web", [ip("10.0.1.10"), ip("10.0.1.11")])
+            .with_members("sg-lb", [ip("10.0.0.5"), ip("10.0.0.6")])
+    }
+
+    fn window() -> (DateTime<Utc>, DateTime<Utc>) {
+        let start = Utc.with_ymd_and_hms(2024, 7, 1, 0, 0, 0).unwrap();
+        (start, start + Duration::days(7))
+    }
+
+    #[test]
+    fn test_minimal_cover_and_unused_rules() {
+        let acl_rule = |n: i32, ports: (i32, i32), cidr: &str| ACLRule {
+            rule_number: n,
+            protocol: Protocol::TCP,
+            rule_action: RuleAction::Allow,
+            cidr_block: cidr.into(),
+            from_port: Some(ports.0),
+            to_port: Some(ports.1),
+            icmp_type: None,
+            icmp_code: None,
+        };
+        let acl = NetworkACL {
+            id: "acl-web".into(),
+            name: "web".into(),
+            vpc_id: "vpc-1".into(),
+            inbound_rules: vec![
+                acl_rule(100, (443, 443), "0.0.0.0/0"),
+                acl_rule(110, (8080, 8081), "10.0.0.0/8"),
+                acl_rule(120, (3389, 3389), "0.0.0.0/0"),
+                acl_rule(130, (22, 22), "0.0.0.0/0"),
+            ],
+            // 110 is shadowed: the database connection already matches the ephemeral range at 100
+            outbound_rules: vec![acl_rule(100, (1024, 65535), "0.0.0.0/0"), acl_rule(110, (5432, 5432), "10.2.0.0/16")],
+            associations: Vec::new(),
+            default: false,
+        };
+        let (start, end) = window();
+        let report = analyzer().analyze(&web_group(), &[acl], &fixture_logs(), start, end).unwrap();
+
+        assert_eq!(report.records_analyzed, 11);
+        // The reply on 8080, the rejected SSH attempt and the unrelated network are not flows
+        assert_eq!(report.flows.len(), 7);
+        let shapes: Vec<_> = report.ingress.iter().map(|r| (r.from_port, r.to_port, r.cidr_blocks.clone(), r.source_groups.clone())).collect();
+        assert_eq!(
+            shapes,
+            vec![
+                (Some(443), Some(443), vec![], vec!["sg-lb".to_string()]),
+                (Some(8080), Some(8081), vec!["10.1.0.4/31".to_string()], vec![]),
+            ]
+        );
+        assert_eq!(report.egress.len(), 1);
+        assert_eq!(report.egress[0].cidr_blocks, vec!["10.2.3.4/32"]);
+        assert_eq!(report.egress[0].from_port, Some(5432));
+
+        let unused: Vec<_> = report.unused_rules.iter().map(|u| u.rule.id.as_str()).collect();
+        assert_eq!(unused, vec!["sgr-dns"]);
+        let unused_acl: Vec<_> = report.unused_acl_rules.iter().map(|u| (u.direction, u.rule_number)).collect();
+        assert_eq!(unused_acl, vec![(Direction::Ingress, 120), (Direction::Egress, 110)]);
+    }
+
+    struct Groups(Mutex<HashMap<String, SecurityGroup>>);
+
+    #[async_trait]
+    impl SecurityGroupManager for Groups {
+        async fn create_security_group(&self, group: SecurityGroup) -> NetworkResult<SecurityGroup> {
+            self.0.lock().unwrap().insert(group.id.clone(), group.clone());
+            Ok(group)
+        }
+
+        async fn update_security_group(&self, group: SecurityGroup) -> NetworkResult<SecurityGroup> {
+            self.create_security_group(group).await
+        }
+
+        async fn delete_security_group(&self, id: &str) -> NetworkResult<()> {
+            self.0.lock().unwrap().remove(id);
+            Ok(())
+        }
+
+        async fn get_security_group(&self, id: &str) -> NetworkResult<SecurityGroup> {
+            self.0.lock().unwrap().get(id).cloned().ok_or_else(|| NetworkError::NotFound(id.into()))
+        }
+
+        async fn list_security_groups(&self) -> NetworkResult<Vec<SecurityGroup>> {
+            Ok(self.0.lock().unwrap().values().cloned().collect())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_apply_requires_confirming_the_dry_run() {
+        let mut group = web_group();
+        // Already exactly what the egress traffic needs, so it is kept as is
+        group.egress_rules = vec![rule("sgr-db", Protocol::TCP, (5432, 5432), &["10.2.3.4/32"], &[])];
+        let groups = Groups(Mutex::new(HashMap::from([(group.id.clone(), group.clone())])));
+        let (start, end) = window();
+        let report = analyzer().analyze(&group, &[], &fixture_logs(), start, end).unwrap();
+        // The first record is on July 1st, so the full week counts: half of the two weeks needed
+        assert_eq!(report.observed_seconds, Duration::days(7).num_seconds());
+        assert!((report.confidence - 0.5).abs() < 1e-9);
+
+        let declined = apply_recommendations(&groups, &report, |_| false).await.unwrap();
+        assert!(declined.applied.is_none());
+        assert_eq!(groups.get_security_group("sg-web").await.unwrap().ingress_rules.len(), 3);
+        assert_eq!(declined.plan.warnings.len(), 1);
+
+        // The load balancer rule is already what the 443 traffic needs
+        let result = apply_recommendations(&groups, &report, |plan| plan.removed.len() == 2).await.unwrap();
+        let applied = result.applied.unwrap();
+        let ids: Vec<_> = applied.ingress_rules.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids, vec!["sgr-lb", "recommended-ingress-2"]);
+        assert_eq!(applied.egress_rules[0].id, "sgr-db");
+        assert_eq!(groups.get_security_group("sg-web").await.unwrap().ingress_rules, applied.ingress_rules);
+    }
+}
@@ -5,6 +5,12 @@ use chrono::{DateTime, Utc};
 
 use crate::error::NetworkResult;
 
+pub mod psk;
+pub mod wireguard;
+
+pub use psk::{PskRotator, TunnelBackend};
+pub use wireguard::{CommandRunner, WireGuardManager};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VpnConnection {
     pub id: String,
@@ -26,6 +32,28 @@ pub enum VpnType {
     DynamicRouting { bgp_asn: u32 },
     PolicyBased,
     RouteBased,
+    WireGuard(WireGuardConfig),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WireGuardConfig {
+    pub interface_name: String,
+    // Key-vault secret holding the base64 interface private key
+    pub private_key_secret: String,
+    pub addresses: Vec<String>,
+    pub listen_port: Option<u16>,
+    pub mtu: Option<u32>,
+    pub peers: Vec<WireGuardPeer>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WireGuardPeer {
+    pub name: Option<String>,
+    pub public_key: String,
+    pub preshared_key_secret: Option<String>,
+    pub endpoint: Option<String>,
+    pub allowed_ips: Vec<String>,
+    pub persistent_keepalive: Option<u16>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -92,12 +120,23 @@ pub struct VpnTunnel {
     pub outside_ip: String,
     pub inside_cidr: String,
     pub preshared_key: String,
+    // Set once the key lives in key-vault; `preshared_key` is cleared at that point
+    #[serde(default)]
+    pub preshared_key_secret: Option<PresharedKeyRef>,
     pub phase1: IkeConfiguration,
     pub phase2: IpsecConfiguration,
     pub last_status_change: DateTime<Utc>,
     pub metrics: Option<TunnelMetrics>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PresharedKeyRef {
+    pub secret_id: String,
+    pub version: i32,
+    pub fingerprint: String,
+    pub rotated_at: DateTime<Utc>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum TunnelStatus {
     Up,
@@ -156,6 +195,10 @@ pub struct TunnelMetrics {
     pub timestamp: DateTime<Utc>,
     pub status_checks: TunnelStatusChecks,
     pub traffic: TunnelTraffic,
+    #[serde(default)]
+    pub peer_public_key: Option<String>,
+    #[serde(default)]
+    pub handshake_age_seconds: Option<i64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -0,0 +1,744 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveTime, Utc};
+use rand::rngs::OsRng;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sirsi_key_vault::secret::{Secret, SecretManager, SecretValue};
+use sirsi_key_vault::KeyVaultError;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::error::{NetworkError, NetworkResult};
+use super::{PresharedKeyRef, VpnManager, VpnTunnel};
+
+// AWS-compatible PSK alphabet; the first character must not be a digit
+const PSK_ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789._";
+const PSK_LEADING: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+// 48 characters from a 64-symbol alphabet is ~288 bits of entropy
+const PSK_LENGTH: usize = 48;
+
+// Debug output never includes the key material
+#[derive(Clone, PartialEq, Eq)]
+pub struct PresharedKey(String);
+
+impl PresharedKey {
+    pub fn generate() -> Self {
+        let mut rng = OsRng;
+        let mut key = String::with_capacity(PSK_LENGTH);
+        key.push(PSK_LEADING[rng.gen_range(0..PSK_LEADING.len())] as char);
+        for _ in 1..PSK_LENGTH {
+            key.push(PSK_ALPHABET[rng.gen_range(0..PSK_ALPHABET.len())] as char);
+        }
+        Self(key)
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    pub fn fingerprint(&self) -> String {
+        fingerprint(&self.0)
+    }
+}
+
+impl fmt::Debug for PresharedKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PresharedKey({})", self.fingerprint())
+    }
+}
+
+pub fn fingerprint(key: &str) -> String {
+    let digest = Sha256::digest(key.as_bytes());
+    format!("SHA256:{}", hex::encode(&digest[..8]))
+}
+
+pub fn psk_secret_id(connection_id: &str, tunnel_id: &str) -> String {
+    format!("vpn/{}/{}/psk", connection_id, tunnel_id)
+}
+
+// Drives security associations on the device terminating the tunnel
+#[async_trait]
+pub trait TunnelBackend: Send + Sync {
+    // Whether a second SA can be negotiated while the current one carries traffic
+    fn supports_parallel_sa(&self) -> bool;
+    async fn current_sa(&self, connection_id: &str, tunnel: &VpnTunnel) -> NetworkResult<Option<String>>;
+    async fn establish_sa(&self, connection_id: &str, tunnel: &VpnTunnel, psk: &PresharedKey) -> NetworkResult<String>;
+    async fn sa_ready(&self, connection_id: &str, tunnel: &VpnTunnel, sa_id: &str) -> NetworkResult<bool>;
+    async fn teardown_sa(&self, connection_id: &str, tunnel: &VpnTunnel, sa_id: &str) -> NetworkResult<()>;
+}
+
+// Daily UTC window in which tunnels that cannot overlap SAs may be bounced
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaintenanceWindow {
+    pub start: NaiveTime,
+    pub duration_minutes: i64,
+}
+
+impl MaintenanceWindow {
+    fn start_on(&self, now: DateTime<Utc>, days: i64) -> DateTime<Utc> {
+        (now.date_naive() + chrono::Duration::days(days)).and_time(self.start).and_utc()
+    }
+
+    pub fn contains(&self, now: DateTime<Utc>) -> bool {
+        let duration = chrono::Duration::minutes(self.duration_minutes);
+        [-1, 0].into_iter().any(|days| {
+            let start = self.start_on(now, days);
+            start <= now && now < start + duration
+        })
+    }
+
+    pub fn next_start(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        if self.contains(now) {
+            return now;
+        }
+        let today = self.start_on(now, 0);
+        if today > now {
+            today
+        } else {
+            self.start_on(now, 1)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RotationStrategy {
+    // New SA up before the old one is torn down
+    Overlap,
+    // Old SA torn down first; the tunnel is briefly down
+    Replace,
+}
+
+// Carries fingerprints only, never key material
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PskRotationEvent {
+    pub connection_id: String,
+    pub tunnel_id: String,
+    pub old_fingerprint: Option<String>,
+    pub new_fingerprint: String,
+    pub secret_id: String,
+    pub secret_version: i32,
+    pub strategy: RotationStrategy,
+    pub rotated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub enum RotationOutcome {
+    Rotated(PskRotationEvent),
+    Scheduled { at: DateTime<Utc> },
+}
+
+#[derive(Debug, Clone)]
+pub struct PskRotationPolicy {
+    pub max_age: chrono::Duration,
+    pub check_interval: Duration,
+}
+
+impl Default for PskRotationPolicy {
+    fn default() -> Self {
+        Self {
+            max_age: chrono::Duration::days(90),
+            check_interval: Duration::from_secs(3600),
+        }
+    }
+}
+
+type TunnelKey = (String, String);
+
+pub struct PskRotator {
+    connections: Arc<dyn VpnManager>,
+    secrets: Arc<dyn SecretManager>,
+    backend: Arc<dyn TunnelBackend>,
+    maintenance_window: Option<MaintenanceWindow>,
+    sa_timeout: Duration,
+    poll_interval: Duration,
+    events: broadcast::Sender<PskRotationEvent>,
+    pending: Mutex<HashMap<TunnelKey, DateTime<Utc>>>,
+    in_flight: Mutex<HashSet<TunnelKey>>,
+}
+
+struct InFlight<'a> {
+    set: &'a Mutex<HashSet<TunnelKey>>,
+    key: TunnelKey,
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.set.lock().expect("rotation lock poisoned").remove(&self.key);
+    }
+}
+
+fn vault_error(secret_id: &str, e: KeyVaultError) -> NetworkError {
+    NetworkError::Vpn(format!("Failed to store preshared key {}: {}", secret_id, e))
+}
+
+impl PskRotator {
+    pub fn new(connections: Arc<dyn VpnManager>, secrets: Arc<dyn SecretManager>, backend: Arc<dyn TunnelBackend>) -> Self {
+        let (events, _) = broadcast::channel(256);
+        Self {
+            connections,
+            secrets,
+            backend,
+            maintenance_window: None,
+            sa_timeout: Duration::from_secs(60),
+            poll_interval: Duration::from_secs(1),
+            events,
+            pending: Mutex::new(HashMap::new()),
+            in_flight: Mutex::new(HashSet::new()),
+        }
+    }
+
+    pub fn with_maintenance_window(mut self, window: MaintenanceWindow) -> Self {
+        self.maintenance_window = Some(window);
+        self
+    }
+
+    pub fn with_sa_timeout(mut self, timeout: Duration, poll_interval: Duration) -> Self {
+        self.sa_timeout = timeout;
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<PskRotationEvent> {
+        self.events.subscribe()
+    }
+
+    pub fn pending(&self) -> HashMap<TunnelKey, DateTime<Utc>> {
+        self.pending.lock().expect("rotation lock poisoned").clone()
+    }
+
+    pub async fn rotate_preshared_key(&self, connection_id: &str, tunnel_id: &str) -> NetworkResult<RotationOutcome> {
+        self.rotate_at(connection_id, tunnel_id, Utc::now()).await
+    }
+
+    async fn rotate_at(&self, connection_id: &str, tunnel_id: &str, now: DateTime<Utc>) -> NetworkResult<RotationOutcome> {
+        let strategy = if self.backend.supports_parallel_sa() {
+            RotationStrategy::Overlap
+        } else {
+            RotationStrategy::Replace
+        };
+        if strategy == RotationStrategy::Replace {
+            if let Some(window) = self.maintenance_window.as_ref().filter(|w| !w.contains(now)) {
+                let at = window.next_start(now);
+                self.pending
+                    .lock()
+                    .expect("rotation lock poisoned")
+                    .insert((connection_id.to_string(), tunnel_id.to_string()), at);
+                info!(connection_id, tunnel_id, %at, "Preshared key rotation scheduled for maintenance window");
+                return Ok(RotationOutcome::Scheduled { at });
+            }
+        }
+        self.rotate_now(connection_id, tunnel_id, strategy, now).await.map(RotationOutcome::Rotated)
+    }
+
+    async fn rotate_now(
+        &self,
+        connection_id: &str,
+        tunnel_id: &str,
+        strategy: RotationStrategy,
+        now: DateTime<Utc>,
+    ) -> NetworkResult<PskRotationEvent> {
+        let key = (connection_id.to_string(), tunnel_id.to_string());
+        if !self.in_flight.lock().expect("rotation lock poisoned").insert(key.clone()) {
+            return Err(NetworkError::Conflict(format!(
+                "Preshared key rotation already running for tunnel {} of {}",
+                tunnel_id, connection_id
+            )));
+        }
+        let _in_flight = InFlight {
+            set: &self.in_flight,
+            key: key.clone(),
+        };
+
+        let mut connection = self.connections.get_connection(connection_id).await?;
+        let tunnel = connection
+            .tunnels
+            .iter()
+            .find(|t| t.id == tunnel_id)
+            .cloned()
+            .ok_or_else(|| NetworkError::NotFound(format!("Tunnel {} not found on {}", tunnel_id, connection_id)))?;
+        let old_fingerprint = match &tunnel.preshared_key_secret {
+            Some(reference) => Some(reference.fingerprint.clone()),
+            None if !tunnel.preshared_key.is_empty() => Some(fingerprint(&tunnel.preshared_key)),
+            None => None,
+        };
+
+        // The key is durable in the vault before any device uses it
+        let psk = PresharedKey::generate();
+        let secret_id = psk_secret_id(connection_id, tunnel_id);
+        let version = self.store(&secret_id, connection_id, tunnel_id, &psk).await?;
+
+        let old_sa = self.backend.current_sa(connection_id, &tunnel).await?;
+        match strategy {
+            RotationStrategy::Overlap => {
+                let new_sa = self.backend.establish_sa(connection_id, &tunnel, &psk).await?;
+                if let Err(e) = self.wait_ready(connection_id, &tunnel, &new_sa).await {
+                    // The old SA is still carrying traffic; abandon the new one
+                    if let Err(teardown) = self.backend.teardown_sa(connection_id, &tunnel, &new_sa).await {
+                        warn!(connection_id, tunnel_id, sa_id = %new_sa, "Failed to tear down unused SA: {}", teardown);
+                    }
+                    return Err(e);
+                }
+                if let Some(old_sa) = &old_sa {
+                    self.backend.teardown_sa(connection_id, &tunnel, old_sa).await?;
+                }
+            }
+            RotationStrategy::Replace => {
+                if let Some(old_sa) = &old_sa {
+                    self.backend.teardown_sa(connection_id, &tunnel, old_sa).await?;
+                }
+                let new_sa = self.backend.establish_sa(connection_id, &tunnel, &psk).await?;
+                self.wait_ready(connection_id, &tunnel, &new_sa).await?;
+            }
+        }
+
+        let new_fingerprint = psk.fingerprint();
+        let stored = connection
+            .tunnels
+            .iter_mut()
+            .find(|t| t.id == tunnel_id)
+            .expect("tunnel was found above");
+        stored.preshared_key = String::new();
+        stored.preshared_key_secret = Some(PresharedKeyRef {
+            secret_id: secret_id.clone(),
+            version,
+            fingerprint: new_fingerprint.clone(),
+            rotated_at: now,
+        });
+        connection.updated_at = Utc::now();
+        self.connections.modify_connection(connection).await?;
+        self.pending.lock().expect("rotation lock poisoned").remove(&key);
+
+        let event = PskRotationEvent {
+            connection_id: connection_id.to_string(),
+            tunnel_id: tunnel_id.to_string(),
+            old_fingerprint,
+            new_fingerprint,
+            secret_id,
+            secret_version: version,
+            strategy,
+            rotated_at: now,
+        };
+        info!(
+            connection_id,
+            tunnel_id,
+            old_fingerprint = event.old_fingerprint.as_deref().unwrap_or("none"),
+            new_fingerprint = %event.new_fingerprint,
+            "Rotated tunnel preshared key"
+        );
+        let _ = self.events.send(event.clone());
+        Ok(event)
+    }
+
+    async fn store(&self, secret_id: &str, connection_id: &str, tunnel_id: &str, psk: &PresharedKey) -> NetworkResult<i32> {
+        let now = Utc::now();
+        let secret = Secret {
+            id: secret_id.to_string(),
+            name: format!("{} {} preshared key", connection_id, tunnel_id),
+            description: Some("Site-to-site VPN tunnel preshared key".to_string()),
+            value: SecretValue::Plain(psk.expose().to_string()),
+            version: 0,
+            created_at: now,
+            updated_at: now,
+            expires_at: None,
+            metadata: HashMap::from([
+                ("connection_id".to_string(), connection_id.to_string()),
+                ("tunnel_id".to_string(), tunnel_id.to_string()),
+                ("fingerprint".to_string(), psk.fingerprint()),
+            ]),
+            labels: HashMap::from([("kind".to_string(), "vpn-psk".to_string())]),
+            rotation_policy: None,
+        };
+        let stored = match self.secrets.get_secret(secret_id).await {
+            Ok(_) => self.secrets.update_secret(secret).await,
+            Err(KeyVaultError::NotFound(_)) => self.secrets.create_secret(secret).await,
+            Err(e) => return Err(vault_error(secret_id, e)),
+        };
+        stored.map(|s| s.version).map_err(|e| vault_error(secret_id, e))
+    }
+
+    async fn wait_ready(&self, connection_id: &str, tunnel: &VpnTunnel, sa_id: &str) -> NetworkResult<()> {
+        let deadline = tokio::time::Instant::now() + self.sa_timeout;
+        loop {
+            if self.backend.sa_ready(connection_id, tunnel, sa_id).await? {
+                return Ok(());
+            }
+            if tokio::time::Instant::now() >= deadline {
+                return Err(NetworkError::Unavailable(format!(
+                    "SA {} for tunnel {} did not come up within {:?}",
+                    sa_id, tunnel.id, self.sa_timeout
+                )));
+            }
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+
+    // Runs scheduled rotations whose window is open and rotates keys older than the policy allows
+    pub async fn run_scheduled(&self, policy: &PskRotationPolicy, now: DateTime<Utc>) -> Vec<NetworkResult<RotationOutcome>> {
+        let mut due: Vec<TunnelKey> = self
+            .pending
+            .lock()
+            .expect("rotation lock poisoned")
+            .iter()
+            .filter(|(_, at)| **at <= now)
+            .map(|(key, _)| key.clone())
+            .collect();
+
+        match self.connections.list_connections().await {
+            Ok(connections) => {
+                let cutoff = now - policy.max_age;
+                for connection in connections {
+                    for tunnel in &connection.tunnels {
+                        let rotated_at = tunnel.preshared_key_secret.as_ref().map(|r| r.rotated_at);
+                        // Keys that predate vault storage have unknown age
+                        let key = (connection.id.clone(), tunnel.id.clone());
+                        if rotated_at.is_none_or(|at| at <= cutoff) && !due.contains(&key) {
+                            due.push(key);
+                        }
+                    }
+                }
+            }
+            Err(e) => warn!("Failed to list VPN connections for PSK rotation: {}", e),
+        }
+
+        let mut outcomes = Vec::new();
+        for (connection_id, tunnel_id) in due {
+            let key = (connection_id.clone(), tunnel_id.clone());
+            let scheduled = self.pending.lock().expect("rotation lock poisoned").contains_key(&key);
+            if scheduled && self.maintenance_window.as_ref().is_some_and(|w| !w.contains(now)) {
+                continue;
+            }
+            let outcome = self.rotate_at(&connection_id, &tunnel_id, now).await;
+            if let Err(e) = &outcome {
+                warn!(connection_id, tunnel_id, "Scheduled preshared key rotation failed: {}", e);
+            }
+            outcomes.push(outcome);
+        }
+        outcomes
+    }
+
+    pub fn spawn_scheduler(self: Arc<Self>, policy: PskRotationPolicy) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(policy.check_interval);
+            loop {
+                ticker.tick().await;
+                self.run_scheduled(&policy, Utc::now()).await;
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vpn::{
+        ConnectionStatus, CustomerGateway, EncryptionAlgorithm, IkeConfiguration, IkeVersion, IntegrityAlgorithm,
+        IpsecConfiguration, IpsecProtocol, RoutingConfiguration, TunnelMetrics, TunnelStatus, VpnConnection,
+        VpnGateway, VpnType,
+    };
+    use sirsi_key_vault::secret::InMemorySecretManager;
+    use std::io::Write;
+    use tokio::sync::RwLock;
+
+    #[derive(Default)]
+    struct Connections {
+        connections: RwLock<HashMap<String, VpnConnection>>,
+    }
+
+    #[async_trait]
+    impl VpnManager for Connections {
+        async fn create_connection(&self, conn: VpnConnection) -> NetworkResult<VpnConnection> {
+            self.connections.write().await.insert(conn.id.clone(), conn.clone());
+            Ok(conn)
+        }
+
+        async fn modify_connection(&self, conn: VpnConnection) -> NetworkResult<VpnConnection> {
+            self.create_connection(conn).await
+        }
+
+        async fn delete_connection(&self, id: &str) -> NetworkResult<()> {
+            self.connections.write().await.remove(id);
+            Ok(())
+        }
+
+        async fn get_connection(&self, id: &str) -> NetworkResult<VpnConnection> {
+            self.connections
+                .read()
+                .await
+                .get(id)
+                .cloned()
+                .ok_or_else(|| NetworkError::NotFound(id.to_string()))
+        }
+
+        async fn list_connections(&self) -> NetworkResult<Vec<VpnConnection>> {
+            Ok(self.connections.read().await.values().cloned().collect())
+        }
+
+        async fn get_connection_metrics(&self, _id: &str) -> NetworkResult<Vec<TunnelMetrics>> {
+            Ok(Vec::new())
+        }
+    }
+
+    struct MockBackend {
+        parallel: bool,
+        ready: bool,
+        ops: Mutex<Vec<String>>,
+        installed: Mutex<Vec<PresharedKey>>,
+        current: Mutex<Option<String>>,
+    }
+
+    impl MockBackend {
+        fn new(parallel: bool, ready: bool) -> Self {
+            Self {
+                parallel,
+                ready,
+                ops: Mutex::new(Vec::new()),
+                installed: Mutex::new(Vec::new()),
+                current: Mutex::new(Some("sa-1".to_string())),
+            }
+        }
+
+        fn ops(&self) -> Vec<String> {
+            self.ops.lock().unwrap().clone()
+        }
+    }
+
+    #[async_trait]
+    impl TunnelBackend for MockBackend {
+        fn supports_parallel_sa(&self) -> bool {
+            self.parallel
+        }
+
+        async fn current_sa(&self, _connection_id: &str, _tunnel: &VpnTunnel) -> NetworkResult<Option<String>> {
+            Ok(self.current.lock().unwrap().clone())
+        }
+
+        async fn establish_sa(&self, _connection_id: &str, _tunnel: &VpnTunnel, psk: &PresharedKey) -> NetworkResult<String> {
+            let mut installed = self.installed.lock().unwrap();
+            installed.push(psk.clone());
+            let sa_id = format!("sa-{}", installed.len() + 1);
+            self.ops.lock().unwrap().push(format!("establish {}", sa_id));
+            Ok(sa_id)
+        }
+
+        async fn sa_ready(&self, _connection_id: &str, _tunnel: &VpnTunnel, sa_id: &str) -> NetworkResult<bool> {
+            self.ops.lock().unwrap().push(format!("ready? {}", sa_id));
+            if self.ready {
+                *self.current.lock().unwrap() = Some(sa_id.to_string());
+            }
+            Ok(self.ready)
+        }
+
+        async fn teardown_sa(&self, _connection_id: &str, _tunnel: &VpnTunnel, sa_id: &str) -> NetworkResult<()> {
+            self.ops.lock().unwrap().push(format!("teardown {}", sa_id));
+            Ok(())
+        }
+    }
+
+    fn tunnel(id: &str, rotated_at: Option<DateTime<Utc>>) -> VpnTunnel {
+        VpnTunnel {
+            id: id.into(),
+            status: TunnelStatus::Up,
+            outside_ip: "203.0.113.10".into(),
+            inside_cidr: "169.254.10.0/30".into(),
+            preshared_key: if rotated_at.is_none() { "legacy.key.from.2019".into() } else { String::new() },
+            preshared_key_secret: rotated_at.map(|at| PresharedKeyRef {
+                secret_id: psk_secret_id("vpn-1", id),
+                version: 1,
+                fingerprint: "SHA256:0000000000000000".into(),
+                rotated_at: at,
+            }),
+            phase1: IkeConfiguration {
+                version: IkeVersion::V2,
+                encryption: EncryptionAlgorithm::AES256GCM,
+                integrity: IntegrityAlgorithm::SHA256,
+                dh_group: 20,
+                lifetime_seconds: 28800,
+            },
+            phase2: IpsecConfiguration {
+                protocol: IpsecProtocol::ESP,
+                encryption: EncryptionAlgorithm::AES256GCM,
+                integrity: IntegrityAlgorithm::SHA256,
+                pfs_group: 20,
+                lifetime_seconds: 3600,
+            },
+            last_status_change: Utc::now(),
+            metrics: None,
+        }
+    }
+
+    async fn connections(tunnels: Vec<VpnTunnel>) -> Arc<Connections> {
+        let connections = Arc::new(Connections::default());
+        connections
+            .create_connection(VpnConnection {
+                id: "vpn-1".into(),
+                name: "branch".into(),
+                connection_type: VpnType::RouteBased,
+                status: ConnectionStatus::Available,
+                customer_gateway: CustomerGateway {
+                    id: "cgw-1".into(),
+                    ip_address: "198.51.100.1".into(),
+                    bgp_asn: None,
+                    device: None,
+                    certificate: None,
+                },
+                vpn_gateway: VpnGateway {
+                    id: "vgw-1".into(),
+                    vpc_id: "vpc-1".into(),
+                    availability_zone: "us-east-1a".into(),
+                    public_ip: "203.0.113.1".into(),
+                    private_ip: "10.0.0.1".into(),
+                },
+                routing: RoutingConfiguration {
+                    propagate_routes: false,
+                    static_routes: vec![],
+                    bgp_config: None,
+                    route_tables: vec![],
+                },
+                tunnels,
+                tags: HashMap::new(),
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+            })
+            .await
+            .unwrap();
+        connections
+    }
+
+    #[derive(Clone, Default)]
+    struct CapturedLogs(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for CapturedLogs {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_overlap_rotation_sequencing() {
+        let logs = CapturedLogs::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::TRACE)
+            .with_writer(move || writer.clone())
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let connections = connections(vec![tunnel("t-1", None)]).await;
+        let secrets = Arc::new(InMemorySecretManager::new());
+        let backend = Arc::new(MockBackend::new(true, true));
+        let rotator = PskRotator::new(connections.clone(), secrets.clone(), backend.clone());
+        let mut events = rotator.subscribe();
+
+        let RotationOutcome::Rotated(event) = rotator.rotate_preshared_key("vpn-1", "t-1").await.unwrap() else {
+            panic!("overlap rotation should not be scheduled");
+        };
+        assert_eq!(backend.ops(), vec!["establish sa-2", "ready? sa-2", "teardown sa-1"]);
+        assert_eq!(event.strategy, RotationStrategy::Overlap);
+        assert_eq!(event.old_fingerprint, Some(fingerprint("legacy.key.from.2019")));
+
+        let psk = backend.installed.lock().unwrap()[0].clone();
+        assert_eq!(psk.expose().len(), PSK_LENGTH);
+        assert_eq!(event.new_fingerprint, psk.fingerprint());
+        let stored = secrets.get_secret(&psk_secret_id("vpn-1", "t-1")).await.unwrap();
+        assert!(matches!(&stored.value, SecretValue::Plain(v) if v == psk.expose()));
+
+        let tunnel = connections.get_connection("vpn-1").await.unwrap().tunnels.remove(0);
+        assert!(tunnel.preshared_key.is_empty());
+        let reference = tunnel.preshared_key_secret.unwrap();
+        assert_eq!((reference.version, reference.fingerprint), (stored.version, psk.fingerprint()));
+        assert_eq!(events.recv().await.unwrap().new_fingerprint, psk.fingerprint());
+
+        let event_json = serde_json::to_string(&event).unwrap();
+        let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        assert!(logs.contains(&psk.fingerprint()));
+        for text in [&logs, &event_json, &format!("{:?}", event), &format!("{:?}", psk)] {
+            assert!(!text.contains(psk.expose()));
+            assert!(!text.contains("legacy.key.from.2019"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_overlap_keeps_old_sa() {
+        let connections = connections(vec![tunnel("t-1", None)]).await;
+        let backend = Arc::new(MockBackend::new(true, false));
+        let rotator = PskRotator::new(connections.clone(), Arc::new(InMemorySecretManager::new()), backend.clone())
+            .with_sa_timeout(Duration::from_millis(20), Duration::from_millis(5));
+
+        let result = rotator.rotate_preshared_key("vpn-1", "t-1").await;
+        assert!(matches!(result, Err(NetworkError::Unavailable(_))));
+        let ops = backend.ops();
+        assert_eq!(ops.first().map(String::as_str), Some("establish sa-2"));
+        assert_eq!(ops.last().map(String::as_str), Some("teardown sa-2"));
+        assert!(!ops.contains(&"teardown sa-1".to_string()));
+
+        let tunnel = connections.get_connection("vpn-1").await.unwrap().tunnels.remove(0);
+        assert_eq!(tunnel.preshared_key, "legacy.key.from.2019");
+        assert!(tunnel.preshared_key_secret.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_replace_waits_for_maintenance_window() {
+        let now = Utc::now();
+        let window = MaintenanceWindow {
+            start: (now + chrono::Duration::hours(2)).time(),
+            duration_minutes: 60,
+        };
+        let recent = now - chrono::Duration::days(1);
+        let connections = connections(vec![tunnel("t-1", Some(recent)), tunnel("t-2", Some(recent))]).await;
+        let backend = Arc::new(MockBackend::new(false, true));
+        let rotator = PskRotator::new(connections.clone(), Arc::new(InMemorySecretManager::new()), backend.clone())
+            .with_maintenance_window(window);
+
+        let outcome = rotator.rotate_at("vpn-1", "t-1", now).await.unwrap();
+        let RotationOutcome::Scheduled { at } = outcome else {
+            panic!("replace rotation outside the window should be scheduled");
+        };
+        assert!(at > now);
+        assert!(backend.ops().is_empty());
+
+        let policy = PskRotationPolicy::default();
+        assert!(rotator.run_scheduled(&policy, now + chrono::Duration::minutes(30)).await.is_empty());
+
+        let outcomes = rotator.run_scheduled(&policy, at + chrono::Duration::minutes(5)).await;
+        assert_eq!(outcomes.len(), 1);
+        assert!(matches!(&outcomes[0], Ok(RotationOutcome::Rotated(e)) if e.strategy == RotationStrategy::Replace));
+        assert_eq!(backend.ops(), vec!["teardown sa-1", "establish sa-2", "ready? sa-2"]);
+        assert!(rotator.pending().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_scheduler_rotates_stale_keys() {
+        let now = Utc::now();
+        let connections = connections(vec![
+            tunnel("stale", Some(now - chrono::Duration::days(120))),
+            tunnel("fresh", Some(now - chrono::Duration::days(10))),
+        ])
+        .await;
+        let rotator = PskRotator::new(
+            connections.clone(),
+            Arc::new(InMemorySecretManager::new()),
+            Arc::new(MockBackend::new(true, true)),
+        );
+
+        let outcomes = rotator.run_scheduled(&PskRotationPolicy::default(), now).await;
+        assert_eq!(outcomes.len(), 1);
+        assert!(matches!(&outcomes[0], Ok(RotationOutcome::Rotated(e)) if e.tunnel_id == "stale"));
+
+        let connection = connections.get_connection("vpn-1").await.unwrap();
+        let fresh = connection.tunnels.iter().find(|t| t.id == "fresh").unwrap();
+        assert_eq!(fresh.preshared_key_secret.as_ref().unwrap().version, 1);
+        assert!(rotator.run_scheduled(&PskRotationPolicy::default(), now).await.is_empty());
+    }
+}
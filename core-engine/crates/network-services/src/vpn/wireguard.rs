@@ -0,0 +1,677 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, TimeZone, Utc};
+use sirsi_key_vault::secret::{SecretManager, SecretValue};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::RwLock;
+use tracing::info;
+
+use crate::error::{NetworkError, NetworkResult};
+use crate::loadbalancer::rules::Cidr;
+use super::{
+    ConnectionStatus, TunnelMetrics, TunnelStatusChecks, TunnelTraffic, VpnConnection, VpnManager, VpnType,
+    WireGuardConfig,
+};
+
+// A peer whose last handshake is older than this has lost its session (REJECT_AFTER_TIME)
+pub const HANDSHAKE_STALE_SECONDS: i64 = 180;
+
+#[derive(Debug, Clone, Default)]
+pub struct CommandOutput {
+    pub status: i32,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+// Runs `wg` and `wg-quick`; swapped out in tests
+#[async_trait]
+pub trait CommandRunner: Send + Sync {
+    async fn run(&self, program: &str, args: &[String], stdin: Option<Vec<u8>>) -> NetworkResult<CommandOutput>;
+}
+
+pub struct SystemCommandRunner;
+
+#[async_trait]
+impl CommandRunner for SystemCommandRunner {
+    async fn run(&self, program: &str, args: &[String], stdin: Option<Vec<u8>>) -> NetworkResult<CommandOutput> {
+        let mut command = tokio::process::Command::new(program);
+        command
+            .args(args)
+            .stdin(if stdin.is_some() { std::process::Stdio::piped() } else { std::process::Stdio::null() })
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped());
+        let mut child = command
+            .spawn()
+            .map_err(|e| NetworkError::Vpn(format!("Failed to run {}: {}", program, e)))?;
+        if let (Some(input), Some(mut pipe)) = (stdin, child.stdin.take()) {
+            pipe.write_all(&input)
+                .await
+                .map_err(|e| NetworkError::Vpn(format!("Failed to write to {}: {}", program, e)))?;
+        }
+        let output = child
+            .wait_with_output()
+            .await
+            .map_err(|e| NetworkError::Vpn(format!("Failed to run {}: {}", program, e)))?;
+        Ok(CommandOutput {
+            status: output.status.code().unwrap_or(-1),
+            stdout: output.stdout,
+            stderr: output.stderr,
+        })
+    }
+}
+
+fn is_wireguard_key(key: &str) -> bool {
+    key.len() == 44
+        && key.ends_with('=')
+        && key.as_bytes()[..43].iter().all(|b| b.is_ascii_alphanumeric() || *b == b'+' || *b == b'/')
+}
+
+pub fn validate_config(config: &WireGuardConfig) -> NetworkResult<()> {
+    let invalid = |msg: String| Err(NetworkError::Validation(msg));
+    let name = &config.interface_name;
+    if name.is_empty()
+        || name.len() > 15
+        || !name.bytes().all(|b| b.is_ascii_alphanumeric() || b"_=+.-".contains(&b))
+    {
+        return invalid(format!("Invalid WireGuard interface name: {}", name));
+    }
+    if config.private_key_secret.is_empty() {
+        return invalid(format!("Interface {} has no private key secret", name));
+    }
+    for address in &config.addresses {
+        Cidr::parse(address)?;
+    }
+
+    let mut public_keys = HashSet::new();
+    let mut claimed: Vec<(Cidr, &str, &str)> = Vec::new();
+    for peer in &config.peers {
+        if !is_wireguard_key(&peer.public_key) {
+            return invalid(format!("Invalid WireGuard public key: {}", peer.public_key));
+        }
+        if !public_keys.insert(peer.public_key.as_str()) {
+            return invalid(format!("Peer {} is listed more than once", peer.public_key));
+        }
+        if let Some(endpoint) = &peer.endpoint {
+            if endpoint.rsplit_once(':').and_then(|(_, port)| port.parse::<u16>().ok()).is_none() {
+                return invalid(format!("Peer endpoint {} must be host:port", endpoint));
+            }
+        }
+        // WireGuard routes by allowed IPs, so two peers claiming the same range is ambiguous
+        for range in &peer.allowed_ips {
+            let cidr = Cidr::parse(range)?;
+            if let Some((_, other_range, other_peer)) = claimed
+                .iter()
+                .find(|(other, _, other_peer)| *other_peer != peer.public_key && other.overlaps(&cidr))
+            {
+                return invalid(format!(
+                    "Allowed IPs {} of peer {} overlap {} of peer {}",
+                    range, peer.public_key, other_range, other_peer
+                ));
+            }
+            claimed.push((cidr, range, &peer.public_key));
+        }
+    }
+    Ok(())
+}
+
+// wg-quick format. `preshared_keys` is keyed by peer public key.
+pub fn render_config(config: &WireGuardConfig, private_key: &str, preshared_keys: &HashMap<String, String>) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "[Interface]");
+    let _ = writeln!(out, "PrivateKey = {}", private_key);
+    if !config.addresses.is_empty() {
+        let _ = writeln!(out, "Address = {}", config.addresses.join(", "));
+    }
+    if let Some(port) = config.listen_port {
+        let _ = writeln!(out, "ListenPort = {}", port);
+    }
+    if let Some(mtu) = config.mtu {
+        let _ = writeln!(out, "MTU = {}", mtu);
+    }
+    for peer in &config.peers {
+        let _ = writeln!(out);
+        let _ = writeln!(out, "[Peer]");
+        if let Some(name) = &peer.name {
+            let _ = writeln!(out, "# {}", name);
+        }
+        let _ = writeln!(out, "PublicKey = {}", peer.public_key);
+        if let Some(psk) = preshared_keys.get(&peer.public_key) {
+            let _ = writeln!(out, "PresharedKey = {}", psk);
+        }
+        if !peer.allowed_ips.is_empty() {
+            let _ = writeln!(out, "AllowedIPs = {}", peer.allowed_ips.join(", "));
+        }
+        if let Some(endpoint) = &peer.endpoint {
+            let _ = writeln!(out, "Endpoint = {}", endpoint);
+        }
+        if let Some(keepalive) = peer.persistent_keepalive {
+            let _ = writeln!(out, "PersistentKeepalive = {}", keepalive);
+        }
+    }
+    out
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerStatus {
+    pub public_key: String,
+    pub endpoint: Option<String>,
+    pub allowed_ips: Vec<String>,
+    pub latest_handshake: Option<DateTime<Utc>>,
+    pub transfer_rx: u64,
+    pub transfer_tx: u64,
+}
+
+// Parses `wg show <interface> dump`. The interface line and preshared key column carry key
+// material and are skipped.
+pub fn parse_dump(output: &str) -> NetworkResult<Vec<PeerStatus>> {
+    let malformed = |line: &str| NetworkError::Vpn(format!("Unexpected wg dump line with {} fields", line.split('\t').count()));
+    let mut peers = Vec::new();
+    for line in output.lines().skip(1).filter(|l| !l.trim().is_empty()) {
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() != 8 {
+            return Err(malformed(line));
+        }
+        let number = |value: &str| value.parse::<u64>().map_err(|_| malformed(line));
+        let handshake = number(fields[4])?;
+        peers.push(PeerStatus {
+            public_key: fields[0].to_string(),
+            endpoint: (fields[2] != "(none)").then(|| fields[2].to_string()),
+            allowed_ips: match fields[3] {
+                "(none)" => Vec::new(),
+                ips => ips.split(',').map(str::to_string).collect(),
+            },
+            latest_handshake: (handshake > 0)
+                .then(|| Utc.timestamp_opt(handshake as i64, 0).single())
+                .flatten(),
+            transfer_rx: number(fields[5])?,
+            transfer_tx: number(fields[6])?,
+        });
+    }
+    Ok(peers)
+}
+
+pub fn peer_metrics(peer: &PeerStatus, now: DateTime<Utc>) -> TunnelMetrics {
+    let handshake_age = peer.latest_handshake.map(|at| (now - at).num_seconds().max(0));
+    let alive = handshake_age.is_some_and(|age| age < HANDSHAKE_STALE_SECONDS);
+    TunnelMetrics {
+        timestamp: now,
+        // WireGuard has no separate key exchange and data phases; a recent handshake covers both
+        status_checks: TunnelStatusChecks {
+            ike_status: alive,
+            ipsec_status: alive,
+            tunnel_status: alive,
+            route_status: !peer.allowed_ips.is_empty(),
+        },
+        traffic: TunnelTraffic {
+            bytes_in: peer.transfer_rx,
+            bytes_out: peer.transfer_tx,
+            packets_in: 0,
+            packets_out: 0,
+            packet_loss: 0.0,
+            latency_ms: 0.0,
+        },
+        peer_public_key: Some(peer.public_key.clone()),
+        handshake_age_seconds: handshake_age,
+    }
+}
+
+fn wireguard_config(conn: &VpnConnection) -> NetworkResult<&WireGuardConfig> {
+    match &conn.connection_type {
+        VpnType::WireGuard(config) => Ok(config),
+        other => Err(NetworkError::Validation(format!(
+            "Connection {} is {:?}, not WireGuard",
+            conn.id, other
+        ))),
+    }
+}
+
+// Manages wg-quick interfaces on the local host, one interface per connection
+pub struct WireGuardManager {
+    secrets: Arc<dyn SecretManager>,
+    runner: Arc<dyn CommandRunner>,
+    config_dir: PathBuf,
+    connections: RwLock<HashMap<String, VpnConnection>>,
+}
+
+impl WireGuardManager {
+    pub fn new(secrets: Arc<dyn SecretManager>, runner: Arc<dyn CommandRunner>) -> Self {
+        Self {
+            secrets,
+            runner,
+            config_dir: PathBuf::from("/etc/wireguard"),
+            connections: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn with_config_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.config_dir = dir.into();
+        self
+    }
+
+    pub fn config_path(&self, interface: &str) -> PathBuf {
+        self.config_dir.join(format!("{}.conf", interface))
+    }
+
+    async fn secret(&self, id: &str) -> NetworkResult<String> {
+        let secret = self
+            .secrets
+            .get_secret(id)
+            .await
+            .map_err(|e| NetworkError::Vpn(format!("Failed to read WireGuard key {}: {}", id, e)))?;
+        match secret.value {
+            SecretValue::Plain(key) => Ok(key.trim().to_string()),
+            _ => Err(NetworkError::Config(format!("WireGuard key {} must be a plain secret", id))),
+        }
+    }
+
+    async fn render(&self, config: &WireGuardConfig) -> NetworkResult<String> {
+        let private_key = self.secret(&config.private_key_secret).await?;
+        let mut preshared_keys = HashMap::new();
+        for peer in &config.peers {
+            if let Some(secret_id) = &peer.preshared_key_secret {
+                preshared_keys.insert(peer.public_key.clone(), self.secret(secret_id).await?);
+            }
+        }
+        Ok(render_config(config, &private_key, &preshared_keys))
+    }
+
+    // Written to a temporary file first so wg-quick never reads a partial config
+    async fn write_config(&self, path: &Path, contents: &str) -> NetworkResult<()> {
+        let io_error = |e: std::io::Error| NetworkError::Vpn(format!("Failed to write {}: {}", path.display(), e));
+        let staging = path.with_extension("conf.tmp");
+        let mut file = tokio::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&staging)
+            .await
+            .map_err(io_error)?;
+        file.write_all(contents.as_bytes()).await.map_err(io_error)?;
+        file.sync_all().await.map_err(io_error)?;
+        tokio::fs::rename(&staging, path).await.map_err(io_error)
+    }
+
+    async fn run(&self, program: &str, args: &[String], stdin: Option<Vec<u8>>) -> NetworkResult<Vec<u8>> {
+        let output = self.runner.run(program, args, stdin).await?;
+        if output.status != 0 {
+            return Err(NetworkError::Vpn(format!(
+                "{} {} exited with {}: {}",
+                program,
+                args.first().map(String::as_str).unwrap_or(""),
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(output.stdout)
+    }
+
+    async fn up(&self, path: &Path) -> NetworkResult<()> {
+        self.run("wg-quick", &["up".to_string(), path.display().to_string()], None).await.map(|_| ())
+    }
+
+    async fn down(&self, path: &Path) -> NetworkResult<()> {
+        self.run("wg-quick", &["down".to_string(), path.display().to_string()], None).await.map(|_| ())
+    }
+}
+
+#[async_trait]
+impl VpnManager for WireGuardManager {
+    async fn create_connection(&self, mut conn: VpnConnection) -> NetworkResult<VpnConnection> {
+        let config = wireguard_config(&conn)?.clone();
+        validate_config(&config)?;
+        let mut connections = self.connections.write().await;
+        if connections.contains_key(&conn.id) {
+            return Err(NetworkError::Conflict(format!("VPN connection {} already exists", conn.id)));
+        }
+        if connections
+            .values()
+            .filter_map(|c| wireguard_config(c).ok())
+            .any(|c| c.interface_name == config.interface_name)
+        {
+            return Err(NetworkError::Conflict(format!("Interface {} is already in use", config.interface_name)));
+        }
+
+        let path = self.config_path(&config.interface_name);
+        self.write_config(&path, &self.render(&config).await?).await?;
+        if let Err(e) = self.up(&path).await {
+            let _ = tokio::fs::remove_file(&path).await;
+            return Err(e);
+        }
+        info!(connection_id = %conn.id, interface = %config.interface_name, peers = config.peers.len(), "WireGuard interface up");
+
+        conn.status = ConnectionStatus::Available;
+        conn.updated_at = Utc::now();
+        connections.insert(conn.id.clone(), conn.clone());
+        Ok(conn)
+    }
+
+    async fn modify_connection(&self, mut conn: VpnConnection) -> NetworkResult<VpnConnection> {
+        let config = wireguard_config(&conn)?.clone();
+        validate_config(&config)?;
+        let mut connections = self.connections.write().await;
+        let existing = connections
+            .get(&conn.id)
+            .ok_or_else(|| NetworkError::NotFound(format!("VPN connection {} not found", conn.id)))?;
+        let previous = wireguard_config(existing)?;
+        if previous.interface_name != config.interface_name {
+            return Err(NetworkError::Validation(format!(
+                "Interface of connection {} cannot be renamed from {} to {}",
+                conn.id, previous.interface_name, config.interface_name
+            )));
+        }
+
+        let path = self.config_path(&config.interface_name);
+        self.write_config(&path, &self.render(&config).await?).await?;
+        // syncconf only applies wg settings; addresses and MTU belong to wg-quick and need a bounce
+        if previous.addresses != config.addresses || previous.mtu != config.mtu {
+            self.down(&path).await?;
+            self.up(&path).await?;
+        } else {
+            let stripped = self.run("wg-quick", &["strip".to_string(), path.display().to_string()], None).await?;
+            self.run(
+                "wg",
+                &["syncconf".to_string(), config.interface_name.clone(), "/dev/stdin".to_string()],
+                Some(stripped),
+            )
+            .await?;
+        }
+
+        conn.status = ConnectionStatus::Available;
+        conn.created_at = existing.created_at;
+        conn.updated_at = Utc::now();
+        connections.insert(conn.id.clone(), conn.clone());
+        Ok(conn)
+    }
+
+    async fn delete_connection(&self, id: &str) -> NetworkResult<()> {
+        let mut connections = self.connections.write().await;
+        let conn = connections
+            .get(id)
+            .ok_or_else(|| NetworkError::NotFound(format!("VPN connection {} not found", id)))?;
+        let path = self.config_path(&wireguard_config(conn)?.interface_name);
+        self.down(&path).await?;
+        tokio::fs::remove_file(&path)
+            .await
+            .map_err(|e| NetworkError::Vpn(format!("Failed to remove {}: {}", path.display(), e)))?;
+        connections.remove(id);
+        Ok(())
+    }
+
+    async fn get_connection(&self, id: &str) -> NetworkResult<VpnConnection> {
+        self.connections
+            .read()
+            .await
+            .get(id)
+            .cloned()
+            .ok_or_else(|| NetworkError::NotFound(format!("VPN connection {} not found", id)))
+    }
+
+    async fn list_connections(&self) -> NetworkResult<Vec<VpnConnection>> {
+        Ok(self.connections.read().await.values().cloned().collect())
+    }
+
+    // One entry per peer
+    async fn get_connection_metrics(&self, id: &str) -> NetworkResult<Vec<TunnelMetrics>> {
+        let interface = wireguard_config(&self.get_connection(id).await?)?.interface_name.clone();
+        let dump = self.run("wg", &["show".to_string(), interface, "dump".to_string()], None).await?;
+        let now = Utc::now();
+        Ok(parse_dump(&String::from_utf8_lossy(&dump))?
+            .iter()
+            .map(|peer| peer_metrics(peer, now))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vpn::{CustomerGateway, RoutingConfiguration, VpnGateway, WireGuardPeer};
+    use sirsi_key_vault::secret::{InMemorySecretManager, Secret};
+    use std::os::unix::fs::PermissionsExt;
+    use std::sync::Mutex;
+
+    const PRIVATE_KEY: &str = "yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=";
+    const NYC_KEY: &str = "xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=";
+    const LON_KEY: &str = "TrMvSoP4jYQlY6RIzBgbssQqY3vxI2Pi+y71lOWWXX0=";
+    const LON_PSK: &str = "E2wJ2ZVdW6BZ1DQoo4xXLmYT8j8Y+gSmuav9j3c6erk=";
+
+    const GOLDEN: &str = "\
+[Interface]
+PrivateKey = yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=
+Address = 10.200.0.1/24, fd00:200::1/64
+ListenPort = 51820
+
+[Peer]
+# branch-nyc
+PublicKey = xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=
+AllowedIPs = 10.1.0.0/16, 10.2.0.0/16
+Endpoint = 198.51.100.1:51820
+PersistentKeepalive = 25
+
+[Peer]
+# branch-lon
+PublicKey = TrMvSoP4jYQlY6RIzBgbssQqY3vxI2Pi+y71lOWWXX0=
+PresharedKey = E2wJ2ZVdW6BZ1DQoo4xXLmYT8j8Y+gSmuav9j3c6erk=
+AllowedIPs = 10.3.0.0/24, fd00:3::/48
+";
+
+    // Captured from `wg show wg-sites dump`
+    const DUMP: &str = "\
+yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=\tHIgo9xNzJMWLKASShiTqIybxZ0U3wGLiUeJ1PKf8ykw=\t51820\toff
+xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=\t(none)\t198.51.100.1:51820\t10.1.0.0/16,10.2.0.0/16\t1700000000\t1048576\t524288\t25
+TrMvSoP4jYQlY6RIzBgbssQqY3vxI2Pi+y71lOWWXX0=\tE2wJ2ZVdW6BZ1DQoo4xXLmYT8j8Y+gSmuav9j3c6erk=\t(none)\t10.3.0.0/24,fd00:3::/48\t0\t0\t0\toff
+";
+
+    fn peer(name: &str, public_key: &str, allowed_ips: &[&str]) -> WireGuardPeer {
+        WireGuardPeer {
+            name: Some(name.into()),
+            public_key: public_key.into(),
+            preshared_key_secret: None,
+            endpoint: None,
+            allowed_ips: allowed_ips.iter().map(|s| s.to_string()).collect(),
+            persistent_keepalive: None,
+        }
+    }
+
+    fn config() -> WireGuardConfig {
+        let mut nyc = peer("branch-nyc", NYC_KEY, &["10.1.0.0/16", "10.2.0.0/16"]);
+        nyc.endpoint = Some("198.51.100.1:51820".into());
+        nyc.persistent_keepalive = Some(25);
+        let mut lon = peer("branch-lon", LON_KEY, &["10.3.0.0/24", "fd00:3::/48"]);
+        lon.preshared_key_secret = Some("wg/lon-psk".into());
+        WireGuardConfig {
+            interface_name: "wg-sites".into(),
+            private_key_secret: "wg/private".into(),
+            addresses: vec!["10.200.0.1/24".into(), "fd00:200::1/64".into()],
+            listen_port: Some(51820),
+            mtu: None,
+            peers: vec![nyc, lon],
+        }
+    }
+
+    fn connection(config: WireGuardConfig) -> VpnConnection {
+        VpnConnection {
+            id: "wg-1".into(),
+            name: "site links".into(),
+            connection_type: VpnType::WireGuard(config),
+            status: ConnectionStatus::Pending,
+            customer_gateway: CustomerGateway {
+                id: "cgw-1".into(),
+                ip_address: "198.51.100.1".into(),
+                bgp_asn: None,
+                device: None,
+                certificate: None,
+            },
+            vpn_gateway: VpnGateway {
+                id: "vgw-1".into(),
+                vpc_id: "vpc-1".into(),
+                availability_zone: "us-east-1a".into(),
+                public_ip: "203.0.113.1".into(),
+                private_ip: "10.0.0.1".into(),
+            },
+            routing: RoutingConfiguration {
+                propagate_routes: false,
+                static_routes: vec![],
+                bgp_config: None,
+                route_tables: vec![],
+            },
+            tunnels: vec![],
+            tags: HashMap::new(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    type Call = (String, Vec<String>, Option<Vec<u8>>);
+
+    #[derive(Default)]
+    struct MockRunner {
+        calls: Mutex<Vec<Call>>,
+    }
+
+    #[async_trait]
+    impl CommandRunner for MockRunner {
+        async fn run(&self, program: &str, args: &[String], stdin: Option<Vec<u8>>) -> NetworkResult<CommandOutput> {
+            self.calls.lock().unwrap().push((program.to_string(), args.to_vec(), stdin));
+            let stdout = match (program, args.first().map(String::as_str)) {
+                ("wg", Some("show")) => DUMP.as_bytes().to_vec(),
+                ("wg-quick", Some("strip")) => b"[Interface]\nListenPort = 51820\n".to_vec(),
+                _ => Vec::new(),
+            };
+            Ok(CommandOutput {
+                status: 0,
+                stdout,
+                stderr: Vec::new(),
+            })
+        }
+    }
+
+    async fn secrets() -> Arc<InMemorySecretManager> {
+        let secrets = Arc::new(InMemorySecretManager::new());
+        for (id, value) in [("wg/private", PRIVATE_KEY), ("wg/lon-psk", LON_PSK)] {
+            secrets
+                .create_secret(Secret {
+                    id: id.into(),
+                    name: id.into(),
+                    description: None,
+                    value: SecretValue::Plain(value.into()),
+                    version: 0,
+                    created_at: Utc::now(),
+                    updated_at: Utc::now(),
+                    expires_at: None,
+                    metadata: HashMap::new(),
+                    labels: HashMap::new(),
+                    rotation_policy: None,
+                })
+                .await
+                .unwrap();
+        }
+        secrets
+    }
+
+    #[test]
+    fn test_render_golden() {
+        let preshared_keys = HashMap::from([(LON_KEY.to_string(), LON_PSK.to_string())]);
+        assert_eq!(render_config(&config(), PRIVATE_KEY, &preshared_keys), GOLDEN);
+
+        let minimal = WireGuardConfig {
+            interface_name: "wg0".into(),
+            private_key_secret: "wg/private".into(),
+            addresses: vec![],
+            listen_port: None,
+            mtu: Some(1420),
+            peers: vec![],
+        };
+        assert_eq!(
+            render_config(&minimal, PRIVATE_KEY, &HashMap::new()),
+            format!("[Interface]\nPrivateKey = {}\nMTU = 1420\n", PRIVATE_KEY)
+        );
+    }
+
+    #[test]
+    fn test_validation_rejects_overlapping_peers() {
+        assert!(validate_config(&config()).is_ok());
+
+        let mut overlapping = config();
+        overlapping.peers[1].allowed_ips.push("10.1.4.0/24".into());
+        let err = validate_config(&overlapping).unwrap_err();
+        assert!(matches!(&err, NetworkError::Validation(msg) if msg.contains("10.1.4.0/24") && msg.contains("10.1.0.0/16")));
+
+        let mut v6 = config();
+        v6.peers[0].allowed_ips.push("fd00:3:0:1::/64".into());
+        assert!(validate_config(&v6).is_err());
+
+        let mut bad_key = config();
+        bad_key.peers[0].public_key = "not-a-key".into();
+        assert!(validate_config(&bad_key).is_err());
+
+        let mut duplicate = config();
+        duplicate.peers[1].public_key = NYC_KEY.into();
+        assert!(validate_config(&duplicate).is_err());
+    }
+
+    #[test]
+    fn test_parse_dump_metrics() {
+        let peers = parse_dump(DUMP).unwrap();
+        assert_eq!(peers.len(), 2);
+        assert_eq!(peers[0].endpoint.as_deref(), Some("198.51.100.1:51820"));
+        assert_eq!(peers[1].allowed_ips, vec!["10.3.0.0/24", "fd00:3::/48"]);
+        assert!(peers[1].latest_handshake.is_none());
+
+        let now = Utc.timestamp_opt(1_700_000_065, 0).unwrap();
+        let nyc = peer_metrics(&peers[0], now);
+        assert_eq!(nyc.handshake_age_seconds, Some(65));
+        assert!(nyc.status_checks.tunnel_status);
+        assert_eq!((nyc.traffic.bytes_in, nyc.traffic.bytes_out), (1_048_576, 524_288));
+        assert_eq!(nyc.peer_public_key.as_deref(), Some(NYC_KEY));
+
+        let lon = peer_metrics(&peers[1], now);
+        assert_eq!(lon.handshake_age_seconds, None);
+        assert!(!lon.status_checks.tunnel_status && lon.status_checks.route_status);
+
+        let stale = peer_metrics(&peers[0], now + chrono::Duration::minutes(5));
+        assert!(!stale.status_checks.tunnel_status);
+
+        assert!(parse_dump("iface\npeer\tonly-two-fields\n").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_connection_lifecycle() {
+        let dir = std::env::temp_dir().join(format!("sirsi-wg-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let runner = Arc::new(MockRunner::default());
+        let manager = WireGuardManager::new(secrets().await, runner.clone()).with_config_dir(&dir);
+
+        manager.create_connection(connection(config())).await.unwrap();
+        let path = dir.join("wg-sites.conf");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), GOLDEN);
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+
+        let mut second = connection(config());
+        second.id = "wg-2".into();
+        assert!(matches!(manager.create_connection(second).await, Err(NetworkError::Conflict(_))));
+
+        // Peer-only changes are applied in place
+        let mut changed = config();
+        changed.peers.remove(1);
+        manager.modify_connection(connection(changed)).await.unwrap();
+        let metrics = manager.get_connection_metrics("wg-1").await.unwrap();
+        assert_eq!(metrics.len(), 2);
+        manager.delete_connection("wg-1").await.unwrap();
+        assert!(!path.exists());
+
+        let calls = runner.calls.lock().unwrap();
+        let commands: Vec<String> = calls.iter().map(|(p, args, _)| format!("{} {}", p, args[0])).collect();
+        assert_eq!(
+            commands,
+            vec!["wg-quick up", "wg-quick strip", "wg syncconf", "wg show", "wg-quick down"]
+        );
+        assert_eq!(calls[2].2.as_deref(), Some(&b"[Interface]\nListenPort = 51820\n"[..]));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,481 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures::stream::StreamExt;
+use reqwest::header::HeaderMap;
+use reqwest::Method;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use sirsi_common::ErrorKind;
+
+use crate::checksum::{self, Checksum};
+use crate::error::{StoreError, StoreResult};
+use crate::signing::{encode_component, encode_path, hmac_sha256};
+use crate::store::{
+    validate_key, validate_part_number, ByteStream, MultipartUpload, ObjectMeta, ObjectStore, PresignMethod, PutOptions,
+    UploadedPart,
+};
+
+const API_VERSION: &str = "2021-08-06";
+const META_PREFIX: &str = "x-ms-meta-";
+
+#[derive(Debug, Clone)]
+pub struct AzureBlobConfig {
+    pub account: String,
+    pub container: String,
+    // `https://<account>.blob.core.windows.net`, or Azurite's `http://127.0.0.1:10000/<account>`
+    pub endpoint: String,
+    // Base64 shared key; signs every request and the SAS tokens `presign` hands out
+    pub account_key: String,
+}
+
+impl AzureBlobConfig {
+    pub fn new(account: impl Into<String>, container: impl Into<String>, account_key: impl Into<String>) -> Self {
+        let account = account.into();
+        Self {
+            endpoint: format!("https://{}.blob.core.windows.net", account),
+            account,
+            container: container.into(),
+            account_key: account_key.into(),
+        }
+    }
+
+    pub fn with_endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.endpoint = endpoint.into().trim_end_matches('/').to_string();
+        self
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct EnumerationResults {
+    #[serde(default)]
+    blobs: BlobItems,
+    next_marker: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct BlobItems {
+    #[serde(rename = "Blob", default)]
+    blob: Vec<BlobItem>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct BlobItem {
+    name: String,
+    properties: BlobProperties,
+    #[serde(default)]
+    metadata: HashMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BlobProperties {
+    #[serde(rename = "Last-Modified")]
+    last_modified: Option<String>,
+    #[serde(rename = "Content-Length")]
+    content_length: Option<u64>,
+    #[serde(rename = "Content-Type")]
+    content_type: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct BlockListResult {
+    #[serde(default)]
+    uncommitted_blocks: Blocks,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Blocks {
+    #[serde(rename = "Block", default)]
+    block: Vec<Block>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Block {
+    name: String,
+    size: u64,
+}
+
+fn parse_http_date(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc2822(value).ok().map(|t| t.with_timezone(&Utc))
+}
+
+// Block ids carry the part number and its digest, so completing with a digest the stored block
+// doesn't have names a block that doesn't exist and Azure rejects the list
+fn block_id(number: u32, sha256: &str) -> StoreResult<String> {
+    let digest = hex::decode(sha256).map_err(|_| StoreError::Invalid(format!("Malformed digest {}", sha256)))?;
+    let mut raw = number.to_be_bytes().to_vec();
+    raw.extend_from_slice(&digest);
+    Ok(BASE64.encode(raw))
+}
+
+fn parse_block_id(id: &str) -> Option<(u32, String)> {
+    let raw = BASE64.decode(id).ok()?;
+    if raw.len() != 36 {
+        return None;
+    }
+    Some((u32::from_be_bytes(raw[..4].try_into().ok()?), hex::encode(&raw[4..])))
+}
+
+// Azure Blob Storage block blobs over the REST API with shared key auth. Multipart uploads are
+// uncommitted blocks on the destination blob, so two concurrent uploads to one key interfere,
+// and an aborted upload's blocks linger until Azure drops them after a week.
+pub struct AzureBlobStore {
+    http: reqwest::Client,
+    config: AzureBlobConfig,
+    key: Vec<u8>,
+    // Path of the endpoint itself, non-empty for path-style endpoints like Azurite's
+    base_path: String,
+}
+
+impl AzureBlobStore {
+    pub fn new(config: AzureBlobConfig) -> StoreResult<Self> {
+        let key = BASE64
+            .decode(&config.account_key)
+            .map_err(|_| StoreError::Invalid(format!("Account key for {} isn't valid base64", config.account)))?;
+        let endpoint = reqwest::Url::parse(&config.endpoint)
+            .map_err(|e| StoreError::Invalid(format!("Invalid endpoint {}: {}", config.endpoint, e)))?;
+        let base_path = endpoint.path().trim_end_matches('/').to_string();
+        Ok(Self { http: reqwest::Client::new(), config, key, base_path })
+    }
+
+    fn blob_path(&self, blob: Option<&str>) -> String {
+        match blob {
+            Some(blob) => format!("/{}/{}", self.config.container, encode_path(blob)),
+            None => format!("/{}", self.config.container),
+        }
+    }
+
+    // Shared key string-to-sign: the standard headers, then x-ms-* headers and the resource
+    fn authorization(&self, method: &Method, path: &str, query: &[(&str, &str)], headers: &[(String, String)], length: usize, content_type: &str) -> String {
+        let mut ms_headers: Vec<(String, &str)> = headers
+            .iter()
+            .filter(|(k, _)| k.starts_with("x-ms-"))
+            .map(|(k, v)| (k.to_ascii_lowercase(), v.as_str()))
+            .collect();
+        ms_headers.sort();
+        let mut params: Vec<(String, &str)> = query.iter().map(|(k, v)| (k.to_ascii_lowercase(), *v)).collect();
+        params.sort();
+
+        let length = if length == 0 { String::new() } else { length.to_string() };
+        let mut to_sign = format!("{}\n\n\n{}\n\n{}\n\n\n\n\n\n\n", method.as_str(), length, content_type);
+        for (name, value) in &ms_headers {
+            to_sign.push_str(&format!("{}:{}\n", name, value));
+        }
+        to_sign.push_str(&format!("/{}{}{}", self.config.account, self.base_path, path));
+        for (name, value) in &params {
+            to_sign.push_str(&format!("\n{}:{}", name, value));
+        }
+        let signature = BASE64.encode(hmac_sha256(&self.key, to_sign.as_bytes()));
+        format!("SharedKey {}:{}", self.config.account, signature)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn call(
+        &self,
+        method: Method,
+        blob: Option<&str>,
+        query: &[(&str, &str)],
+        mut headers: Vec<(String, String)>,
+        content_type: Option<&str>,
+        body: Bytes,
+        what: &str,
+    ) -> StoreResult<reqwest::Response> {
+        let path = self.blob_path(blob);
+        headers.push(("x-ms-date".into(), Utc::now().format("%a, %d %b %Y %H:%M:%S GMT").to_string()));
+        headers.push(("x-ms-version".into(), API_VERSION.into()));
+        let authorization = self.authorization(&method, &path, query, &headers, body.len(), content_type.unwrap_or_default());
+
+        let mut request = self
+            .http
+            .request(method, format!("{}{}", self.config.endpoint, path))
+            .query(query)
+            .header(reqwest::header::AUTHORIZATION, authorization);
+        for (name, value) in &headers {
+            request = request.header(name.as_str(), value.as_str());
+        }
+        if let Some(content_type) = content_type {
+            request = request.header(reqwest::header::CONTENT_TYPE, content_type);
+        }
+        let response = request
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| StoreError::Unavailable(format!("Failed to {}: {}", what, e)))?;
+        if response.status().is_success() {
+            return Ok(response);
+        }
+        let status = response.status().as_u16();
+        let code = response
+            .headers()
+            .get("x-ms-error-code")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        let message = format!("Failed to {}: {} {}", what, status, code);
+        Err(match code.as_str() {
+            "InvalidBlockList" | "Md5Mismatch" | "Crc64Mismatch" => StoreError::ChecksumMismatch(message),
+            _ => StoreError::from_kind(ErrorKind::from_http_status(status), message),
+        })
+    }
+
+    fn meta_headers(metadata: &HashMap<String, String>) -> Vec<(String, String)> {
+        metadata.iter().map(|(k, v)| (format!("{}{}", META_PREFIX, k), v.clone())).collect()
+    }
+
+    fn meta_from_headers(key: &str, headers: &HeaderMap) -> ObjectMeta {
+        let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+        let mut metadata: HashMap<String, String> = headers
+            .iter()
+            .filter_map(|(name, value)| {
+                Some((name.as_str().strip_prefix(META_PREFIX)?.to_string(), value.to_str().ok()?.to_string()))
+            })
+            .collect();
+        ObjectMeta {
+            key: key.to_string(),
+            size: header("content-length").and_then(|v| v.parse().ok()).unwrap_or_default(),
+            last_modified: header("last-modified").and_then(parse_http_date),
+            content_type: header("content-type").map(str::to_string),
+            checksum: Checksum::from_metadata(&mut metadata),
+            metadata,
+        }
+    }
+
+    async fn xml<T: for<'de> Deserialize<'de>>(response: reqwest::Response) -> StoreResult<T> {
+        let body = response.text().await.map_err(|e| StoreError::Unavailable(format!("Response interrupted: {}", e)))?;
+        quick_xml::de::from_str(&body).map_err(|e| StoreError::Backend(format!("Unexpected response: {}", e)))
+    }
+
+    pub async fn create_container(&self) -> StoreResult<()> {
+        let result = self
+            .call(Method::PUT, None, &[("restype", "container")], Vec::new(), None, Bytes::new(), "create container")
+            .await;
+        match result {
+            Ok(_) | Err(StoreError::Conflict(_)) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    // Service SAS for one blob, signed with the account key
+    fn sas_url(&self, key: &str, method: PresignMethod, expires_in: Duration, now: DateTime<Utc>) -> StoreResult<String> {
+        let expires = chrono::Duration::from_std(expires_in).map_err(|_| StoreError::Invalid("Expiry out of range".into()))?;
+        let expiry = (now + expires).format("%Y-%m-%dT%H:%M:%SZ").to_string();
+        let permissions = match method {
+            PresignMethod::Get => "r",
+            PresignMethod::Put => "cw",
+        };
+        let resource = format!("/blob/{}/{}/{}", self.config.account, self.config.container, key);
+        // sp, st, se, resource, si, sip, spr, sv, sr, snapshot time, encryption scope, then the
+        // five response header overrides
+        let to_sign = [permissions, "", &expiry, &resource, "", "", "", API_VERSION, "b", "", "", "", "", "", "", ""].join("\n");
+        let signature = BASE64.encode(hmac_sha256(&self.key, to_sign.as_bytes()));
+        Ok(format!(
+            "{}{}?sv={}&se={}&sr=b&sp={}&sig={}",
+            self.config.endpoint,
+            self.blob_path(Some(key)),
+            API_VERSION,
+            encode_component(&expiry),
+            permissions,
+            encode_component(&signature)
+        ))
+    }
+}
+
+#[async_trait]
+impl ObjectStore for AzureBlobStore {
+    fn url(&self, key: &str) -> String {
+        format!("{}{}", self.config.endpoint, self.blob_path(Some(key)))
+    }
+
+    async fn put(&self, key: &str, data: Bytes, options: &PutOptions) -> StoreResult<ObjectMeta> {
+        validate_key(key)?;
+        let mut metadata = options.metadata.clone();
+        Checksum::of(&data).to_metadata(&mut metadata);
+        let mut headers = Self::meta_headers(&metadata);
+        headers.push(("x-ms-blob-type".into(), "BlockBlob".into()));
+        self.call(Method::PUT, Some(key), &[], headers, options.content_type.as_deref(), data, &format!("upload {}", key))
+            .await?;
+        self.head(key).await
+    }
+
+    async fn head(&self, key: &str) -> StoreResult<ObjectMeta> {
+        validate_key(key)?;
+        let response = self.call(Method::HEAD, Some(key), &[], Vec::new(), None, Bytes::new(), &format!("read {}", key)).await?;
+        Ok(Self::meta_from_headers(key, response.headers()))
+    }
+
+    async fn get(&self, key: &str) -> StoreResult<(ObjectMeta, ByteStream)> {
+        validate_key(key)?;
+        let response = self.call(Method::GET, Some(key), &[], Vec::new(), None, Bytes::new(), &format!("read {}", key)).await?;
+        let meta = Self::meta_from_headers(key, response.headers());
+        let stream = response
+            .bytes_stream()
+            .map(|chunk| chunk.map_err(|e| StoreError::Unavailable(format!("Download interrupted: {}", e))))
+            .boxed();
+        let stream = checksum::verified(&meta, stream);
+        Ok((meta, stream))
+    }
+
+    async fn delete(&self, key: &str) -> StoreResult<()> {
+        validate_key(key)?;
+        match self.call(Method::DELETE, Some(key), &[], Vec::new(), None, Bytes::new(), &format!("delete {}", key)).await {
+            Ok(_) | Err(StoreError::NotFound(_)) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn list(&self, prefix: &str) -> StoreResult<Vec<ObjectMeta>> {
+        let mut objects = Vec::new();
+        let mut marker = String::new();
+        loop {
+            let mut query = vec![("restype", "container"), ("comp", "list"), ("include", "metadata"), ("prefix", prefix)];
+            if !marker.is_empty() {
+                query.push(("marker", &marker));
+            }
+            let response = self.call(Method::GET, None, &query, Vec::new(), None, Bytes::new(), &format!("list {}", prefix)).await?;
+            let page: EnumerationResults = Self::xml(response).await?;
+            for mut blob in page.blobs.blob {
+                let checksum = Checksum::from_metadata(&mut blob.metadata);
+                objects.push(ObjectMeta {
+                    key: blob.name,
+                    size: blob.properties.content_length.unwrap_or_default(),
+                    last_modified: blob.properties.last_modified.as_deref().and_then(parse_http_date),
+                    content_type: blob.properties.content_type,
+                    checksum,
+                    metadata: blob.metadata,
+                });
+            }
+            marker = page.next_marker.unwrap_or_default();
+            if marker.is_empty() {
+                break;
+            }
+        }
+        objects.sort_by(|a, b| a.key.cmp(&b.key));
+        Ok(objects)
+    }
+
+    async fn presign(&self, key: &str, method: PresignMethod, expires_in: Duration) -> StoreResult<String> {
+        validate_key(key)?;
+        self.sas_url(key, method, expires_in, Utc::now())
+    }
+
+    async fn create_multipart(&self, key: &str, part_size: u64, options: &PutOptions) -> StoreResult<MultipartUpload> {
+        validate_key(key)?;
+        if part_size == 0 {
+            return Err(StoreError::Invalid("Part size must be positive".into()));
+        }
+        Ok(MultipartUpload {
+            key: key.to_string(),
+            upload_id: uuid::Uuid::new_v4().simple().to_string(),
+            part_size,
+            options: options.clone(),
+        })
+    }
+
+    async fn upload_part(&self, upload: &MultipartUpload, number: u32, data: Bytes) -> StoreResult<UploadedPart> {
+        validate_part_number(number)?;
+        let sha256 = hex::encode(Sha256::digest(&data));
+        let id = block_id(number, &sha256)?;
+        let size = data.len() as u64;
+        let query = [("comp", "block"), ("blockid", id.as_str())];
+        self.call(Method::PUT, Some(&upload.key), &query, Vec::new(), None, data, &format!("upload part of {}", upload.key))
+            .await?;
+        Ok(UploadedPart { number, size, sha256, etag: Some(id) })
+    }
+
+    async fn list_parts(&self, upload: &MultipartUpload) -> StoreResult<Vec<UploadedPart>> {
+        let query = [("comp", "blocklist"), ("blocklisttype", "uncommitted")];
+        let what = format!("list parts of {}", upload.key);
+        let response = match self.call(Method::GET, Some(&upload.key), &query, Vec::new(), None, Bytes::new(), &what).await {
+            Ok(response) => response,
+            // No blocks yet and no committed blob either
+            Err(StoreError::NotFound(_)) => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let list: BlockListResult = Self::xml(response).await?;
+        let mut parts: Vec<UploadedPart> = list
+            .uncommitted_blocks
+            .block
+            .into_iter()
+            .filter_map(|block| {
+                let (number, sha256) = parse_block_id(&block.name)?;
+                Some(UploadedPart { number, size: block.size, sha256, etag: Some(block.name) })
+            })
+            .collect();
+        parts.sort_by_key(|p| p.number);
+        Ok(parts)
+    }
+
+    async fn complete_multipart(&self, upload: &MultipartUpload, parts: &[UploadedPart]) -> StoreResult<ObjectMeta> {
+        let checksum = Checksum::multipart(upload, parts)?;
+        let mut body = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?><BlockList>");
+        for part in parts {
+            body.push_str(&format!("<Uncommitted>{}</Uncommitted>", block_id(part.number, &part.sha256)?));
+        }
+        body.push_str("</BlockList>");
+
+        let mut metadata = upload.options.metadata.clone();
+        checksum.to_metadata(&mut metadata);
+        let mut headers = Self::meta_headers(&metadata);
+        if let Some(content_type) = &upload.options.content_type {
+            headers.push(("x-ms-blob-content-type".into(), content_type.clone()));
+        }
+        let query = [("comp", "blocklist")];
+        let what = format!("complete upload of {}", upload.key);
+        self.call(Method::PUT, Some(&upload.key), &query, headers, Some("application/xml"), Bytes::from(body), &what)
+            .await?;
+        self.head(&upload.key).await
+    }
+
+    async fn abort_multipart(&self, _upload: &MultipartUpload) -> StoreResult<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Azurite's well-known development account
+    const DEV_ACCOUNT: &str = "devstoreaccount1";
+    const DEV_KEY: &str = "Eby8vdM02xNOcqFlqUwJPLlmEtlCDXJ1OUzFT50uSRZ6IFsuFq2UVErCz4I6tq/K1SZFPTOtr/KBHBeksoGMGw==";
+
+    #[test]
+    fn test_block_ids_and_sas() {
+        let sha256 = "ab".repeat(32);
+        let id = block_id(7, &sha256).unwrap();
+        assert_eq!(parse_block_id(&id), Some((7, sha256.clone())));
+        // Every id has the same length, as Azure requires within a blob
+        assert_eq!(id.len(), block_id(10_000, &sha256).unwrap().len());
+        assert_eq!(parse_block_id("Zm9yZWlnbg=="), None);
+
+        let config = AzureBlobConfig::new(DEV_ACCOUNT, "exports", DEV_KEY).with_endpoint("http://127.0.0.1:10000/devstoreaccount1/");
+        let store = AzureBlobStore::new(config).unwrap();
+        let now = DateTime::parse_from_rfc3339("2026-03-01T12:00:00Z").unwrap().with_timezone(&Utc);
+        let url = store.sas_url("q1/report.csv", PresignMethod::Get, Duration::from_secs(900), now).unwrap();
+        assert!(url.starts_with("http://127.0.0.1:10000/devstoreaccount1/exports/q1/report.csv?sv=2021-08-06&se=2026-03-01T12%3A15%3A00Z&sr=b&sp=r&sig="));
+        assert!(AzureBlobStore::new(AzureBlobConfig::new(DEV_ACCOUNT, "exports", "not base64!")).is_err());
+    }
+
+    // Needs Azurite, e.g. `docker run -p 10000:10000 mcr.microsoft.com/azure-storage/azurite
+    // azurite-blob --blobHost 0.0.0.0`, at SIRSI_TEST_AZURITE_ENDPOINT
+    // (default http://127.0.0.1:10000/devstoreaccount1)
+    #[cfg(feature = "azurite-tests")]
+    #[tokio::test]
+    async fn test_conformance() {
+        let endpoint = std::env::var("SIRSI_TEST_AZURITE_ENDPOINT")
+            .unwrap_or_else(|_| "http://127.0.0.1:10000/devstoreaccount1".into());
+        let container = format!("sirsi-conformance-{}", &uuid::Uuid::new_v4().simple().to_string()[..8]);
+        let store = AzureBlobStore::new(AzureBlobConfig::new(DEV_ACCOUNT, container, DEV_KEY).with_endpoint(endpoint)).unwrap();
+        store.create_container().await.unwrap();
+        crate::conformance::run(std::sync::Arc::new(store)).await;
+    }
+}
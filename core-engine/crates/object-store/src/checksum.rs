@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::error::{StoreError, StoreResult};
+use crate::store::{ByteStream, MultipartUpload, ObjectMeta, UploadedPart};
+
+// Metadata keys the checksum is recorded under, where a backend has no native field for it
+pub const SHA256_KEY: &str = "sirsi_sha256";
+pub const PARTS_KEY: &str = "sirsi_parts";
+pub const PART_SIZE_KEY: &str = "sirsi_part_size";
+
+// SHA-256 of an object's bytes. Multipart objects are hashed the way S3 composes part
+// checksums: the digest of the concatenated binary part digests, with the part count and size
+// needed to recompute it while reading.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checksum {
+    pub sha256: String,
+    pub parts: Option<(u32, u64)>,
+}
+
+impl Checksum {
+    pub fn of(data: &[u8]) -> Self {
+        Self { sha256: hex::encode(Sha256::digest(data)), parts: None }
+    }
+
+    // Also checks the parts make up a whole upload: numbered 1..=n, all but the last `part_size` long
+    pub fn multipart(upload: &MultipartUpload, parts: &[UploadedPart]) -> StoreResult<Self> {
+        if parts.is_empty() {
+            return Err(StoreError::Invalid(format!("Upload of {} has no parts", upload.key)));
+        }
+        let mut composite = Sha256::new();
+        for (i, part) in parts.iter().enumerate() {
+            let last = i + 1 == parts.len();
+            if part.number as usize != i + 1 {
+                return Err(StoreError::Invalid(format!(
+                    "Upload of {} is missing part {} (got part {})",
+                    upload.key,
+                    i + 1,
+                    part.number
+                )));
+            }
+            if (!last && part.size != upload.part_size) || part.size > upload.part_size {
+                return Err(StoreError::Invalid(format!(
+                    "Part {} of {} is {} bytes, expected {}",
+                    part.number, upload.key, part.size, upload.part_size
+                )));
+            }
+            let digest = hex::decode(&part.sha256)
+                .map_err(|_| StoreError::Invalid(format!("Part {} of {} has a malformed digest", part.number, upload.key)))?;
+            composite.update(digest);
+        }
+        Ok(Self { sha256: hex::encode(composite.finalize()), parts: Some((parts.len() as u32, upload.part_size)) })
+    }
+
+    pub fn to_metadata(&self, metadata: &mut HashMap<String, String>) {
+        metadata.insert(SHA256_KEY.to_string(), self.sha256.clone());
+        if let Some((count, size)) = self.parts {
+            metadata.insert(PARTS_KEY.to_string(), count.to_string());
+            metadata.insert(PART_SIZE_KEY.to_string(), size.to_string());
+        }
+    }
+
+    // Takes the checksum keys out of `metadata`, leaving only the caller's own
+    pub fn from_metadata(metadata: &mut HashMap<String, String>) -> Option<Self> {
+        let sha256 = metadata.remove(SHA256_KEY);
+        let parts = metadata.remove(PARTS_KEY).and_then(|p| p.parse().ok());
+        let part_size = metadata.remove(PART_SIZE_KEY).and_then(|s| s.parse().ok());
+        Some(Self { sha256: sha256?, parts: parts.zip(part_size).filter(|(_, size)| *size > 0) })
+    }
+}
+
+// `sha256:<hex>`, plus `-<parts>x<part size>` for multipart objects
+impl fmt::Display for Checksum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "sha256:{}", self.sha256)?;
+        if let Some((count, size)) = self.parts {
+            write!(f, "-{}x{}", count, size)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Checksum {
+    type Err = StoreError;
+
+    fn from_str(s: &str) -> StoreResult<Self> {
+        let invalid = || StoreError::Invalid(format!("Malformed checksum '{}'", s));
+        let rest = s.strip_prefix("sha256:").ok_or_else(invalid)?;
+        let (sha256, parts) = match rest.split_once('-') {
+            Some((sha256, parts)) => {
+                let (count, size) = parts.split_once('x').ok_or_else(invalid)?;
+                (sha256, Some((count.parse().map_err(|_| invalid())?, size.parse().map_err(|_| invalid())?)))
+            }
+            None => (rest, None),
+        };
+        if sha256.len() != 64 || !sha256.bytes().all(|b| b.is_ascii_hexdigit()) || parts.is_some_and(|(_, size)| size == 0) {
+            return Err(invalid());
+        }
+        Ok(Self { sha256: sha256.to_ascii_lowercase(), parts })
+    }
+}
+
+// Recomputes a checksum over bytes fed in arbitrary chunks
+pub struct Verifier {
+    expected: Checksum,
+    hasher: Sha256,
+    part_digests: Sha256,
+    part_len: u64,
+    parts_seen: u32,
+}
+
+impl Verifier {
+    pub fn new(expected: Checksum) -> Self {
+        Self { expected, hasher: Sha256::new(), part_digests: Sha256::new(), part_len: 0, parts_seen: 0 }
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        let Some((_, part_size)) = self.expected.parts else {
+            self.hasher.update(data);
+            return;
+        };
+        while !data.is_empty() {
+            let take = ((part_size - self.part_len) as usize).min(data.len());
+            self.hasher.update(&data[..take]);
+            self.part_len += take as u64;
+            data = &data[take..];
+            if self.part_len == part_size {
+                self.finish_part();
+            }
+        }
+    }
+
+    fn finish_part(&mut self) {
+        let digest = std::mem::take(&mut self.hasher).finalize();
+        self.part_digests.update(digest);
+        self.part_len = 0;
+        self.parts_seen += 1;
+    }
+
+    pub fn finish(mut self, key: &str) -> StoreResult<()> {
+        let actual = match self.expected.parts {
+            None => hex::encode(self.hasher.finalize()),
+            Some((count, _)) => {
+                if self.part_len > 0 {
+                    self.finish_part();
+                }
+                if self.parts_seen != count {
+                    return Err(StoreError::ChecksumMismatch(format!(
+                        "{} has {} parts, expected {}",
+                        key, self.parts_seen, count
+                    )));
+                }
+                hex::encode(self.part_digests.finalize())
+            }
+        };
+        if actual != self.expected.sha256 {
+            return Err(StoreError::ChecksumMismatch(format!(
+                "{} hashes to {}, expected {}",
+                key, actual, self.expected.sha256
+            )));
+        }
+        Ok(())
+    }
+}
+
+// Passes `stream` through, ending it with a `ChecksumMismatch` error instead of a clean end
+// when the bytes don't match the object's checksum. Objects without one pass unchecked.
+pub fn verified(meta: &ObjectMeta, stream: ByteStream) -> ByteStream {
+    let Some(expected) = meta.checksum.clone() else {
+        return stream;
+    };
+    let state = Some((stream, Verifier::new(expected), meta.key.clone()));
+    stream::unfold(state, |state| async move {
+        let (mut inner, mut verifier, key) = state?;
+        match inner.next().await {
+            Some(Ok(chunk)) => {
+                verifier.update(&chunk);
+                Some((Ok(chunk), Some((inner, verifier, key))))
+            }
+            Some(Err(e)) => Some((Err(e), None)),
+            None => verifier.finish(&key).err().map(|e| (Err(e), None)),
+        }
+    })
+    .boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+
+    fn upload(part_size: u64) -> MultipartUpload {
+        MultipartUpload { key: "k".into(), upload_id: "u".into(), part_size, options: Default::default() }
+    }
+
+    fn part(number: u32, data: &[u8]) -> UploadedPart {
+        UploadedPart { number, size: data.len() as u64, sha256: Checksum::of(data).sha256, etag: None }
+    }
+
+    #[tokio::test]
+    async fn test_multipart_checksum_verifies_across_chunk_boundaries() {
+        let data: Vec<u8> = (0..2500u32).map(|i| (i % 251) as u8).collect();
+        let parts = [part(1, &data[..1000]), part(2, &data[1000..2000]), part(3, &data[2000..])];
+        let checksum = Checksum::multipart(&upload(1000), &parts).unwrap();
+        assert_eq!(checksum.to_string().parse::<Checksum>().unwrap(), checksum);
+
+        // Chunks that straddle part boundaries
+        let read = |bytes: Vec<u8>, checksum: Checksum| {
+            let chunks: Vec<StoreResult<Bytes>> = bytes.chunks(333).map(|c| Ok(Bytes::copy_from_slice(c))).collect();
+            let meta = ObjectMeta {
+                key: "k".into(),
+                size: bytes.len() as u64,
+                last_modified: None,
+                content_type: None,
+                checksum: Some(checksum),
+                metadata: HashMap::new(),
+            };
+            verified(&meta, stream::iter(chunks).boxed()).collect::<Vec<_>>()
+        };
+        assert!(read(data.clone(), checksum.clone()).await.iter().all(|c| c.is_ok()));
+
+        let mut corrupted = data.clone();
+        corrupted[1500] ^= 1;
+        let items = read(corrupted, checksum).await;
+        assert!(matches!(items.last(), Some(Err(StoreError::ChecksumMismatch(_)))));
+        assert_eq!(items.len(), 2500usize.div_ceil(333) + 1);
+
+        let gap = [part(1, &data[..1000]), part(3, &data[2000..])];
+        assert!(matches!(Checksum::multipart(&upload(1000), &gap), Err(StoreError::Invalid(_))));
+        let short = [part(1, &data[..999]), part(2, &data[999..1999])];
+        assert!(matches!(Checksum::multipart(&upload(1000), &short), Err(StoreError::Invalid(_))));
+    }
+
+    #[test]
+    fn test_metadata_round_trip() {
+        let checksum = Checksum { sha256: "ab".repeat(32), parts: Some((3, 1000)) };
+        let mut metadata = HashMap::from([("owner".to_string(), "exports".to_string())]);
+        checksum.to_metadata(&mut metadata);
+        assert_eq!(Checksum::from_metadata(&mut metadata), Some(checksum));
+        assert_eq!(metadata, HashMap::from([("owner".to_string(), "exports".to_string())]));
+        assert!("md5:abc".parse::<Checksum>().is_err());
+    }
+}
@@ -0,0 +1,156 @@
+// Behaviour every backend has to share, run by each backend's tests against a real or emulated
+// store. Everything is written under a fresh prefix and deleted again.
+use std::sync::Arc;
+use std::time::Duration;
+
+use bytes::Bytes;
+use futures::stream::{self, StreamExt};
+
+use crate::checksum::Checksum;
+use crate::error::{StoreError, StoreResult};
+use crate::store::{ObjectStore, PresignMethod, PutOptions};
+use crate::upload::{Uploader, MIN_PART_SIZE};
+
+const PART: u64 = MIN_PART_SIZE;
+
+fn payload(len: usize, seed: u32) -> Vec<u8> {
+    (0..len as u32).map(|i| (i.wrapping_add(seed).wrapping_mul(2654435761) >> 24) as u8).collect()
+}
+
+async fn read(store: &dyn ObjectStore, key: &str) -> StoreResult<Vec<u8>> {
+    let (_, mut chunks) = store.get(key).await?;
+    let mut out = Vec::new();
+    while let Some(chunk) = chunks.next().await {
+        out.extend_from_slice(&chunk?);
+    }
+    Ok(out)
+}
+
+pub(crate) async fn run(store: Arc<dyn ObjectStore>) {
+    let prefix = format!("conformance-{}/", &uuid::Uuid::new_v4().simple().to_string()[..8]);
+    round_trip(store.as_ref(), &prefix).await;
+    listing_and_delete(store.as_ref(), &prefix).await;
+    streamed_multipart(&store, &prefix).await;
+    resumed_upload(&store, &prefix).await;
+    corrupted_part_rejected(store.as_ref(), &prefix).await;
+    presign(store.as_ref(), &prefix).await;
+
+    for object in store.list(&prefix).await.unwrap() {
+        store.delete(&object.key).await.unwrap();
+    }
+    assert!(store.list(&prefix).await.unwrap().is_empty());
+}
+
+async fn round_trip(store: &dyn ObjectStore, prefix: &str) {
+    let key = format!("{}exports/report.csv", prefix);
+    let data = b"id,name\n1,orders\n".to_vec();
+    let options = PutOptions::new().with_content_type("text/csv").with_metadata("owner", "conformance");
+    store.put(&key, Bytes::from(data.clone()), &options).await.unwrap();
+
+    let meta = store.head(&key).await.unwrap();
+    assert_eq!(meta.size, data.len() as u64);
+    assert_eq!(meta.content_type.as_deref(), Some("text/csv"));
+    assert_eq!(meta.metadata.get("owner").map(String::as_str), Some("conformance"));
+    assert_eq!(meta.checksum, Some(Checksum::of(&data)));
+    assert_eq!(read(store, &key).await.unwrap(), data);
+
+    store.put(&key, Bytes::from_static(b"replaced"), &options).await.unwrap();
+    assert_eq!(read(store, &key).await.unwrap(), b"replaced");
+}
+
+async fn listing_and_delete(store: &dyn ObjectStore, prefix: &str) {
+    for name in ["a/1", "a/2", "b/1"] {
+        store.put(&format!("{}{}", prefix, name), Bytes::from(name.as_bytes().to_vec()), &PutOptions::new()).await.unwrap();
+    }
+    let listed: Vec<String> = store.list(&format!("{}a/", prefix)).await.unwrap().into_iter().map(|o| o.key).collect();
+    assert_eq!(listed, [format!("{}a/1", prefix), format!("{}a/2", prefix)]);
+
+    let key = format!("{}a/1", prefix);
+    store.delete(&key).await.unwrap();
+    store.delete(&key).await.unwrap();
+    assert!(matches!(store.head(&key).await, Err(StoreError::NotFound(_))));
+    assert!(matches!(store.get(&key).await, Err(StoreError::NotFound(_))));
+    assert!(matches!(store.put("../escape", Bytes::new(), &PutOptions::new()).await, Err(StoreError::Invalid(_))));
+}
+
+async fn streamed_multipart(store: &Arc<dyn ObjectStore>, prefix: &str) {
+    let uploader = Uploader::new(store.clone()).with_part_size(PART);
+    let data = payload(2 * PART as usize + 1234, 7);
+    let chunks: Vec<StoreResult<Bytes>> = data.chunks(64 * 1024).map(|c| Ok(Bytes::copy_from_slice(c))).collect();
+    let key = format!("{}backups/large.dump", prefix);
+    let meta = uploader.upload(&key, stream::iter(chunks).boxed(), &PutOptions::new()).await.unwrap();
+    assert_eq!(meta.size, data.len() as u64);
+    assert_eq!(meta.checksum.as_ref().and_then(|c| c.parts), Some((3, PART)));
+    assert_eq!(store.head(&key).await.unwrap().checksum, meta.checksum);
+    assert!(read(store.as_ref(), &key).await.unwrap() == data);
+
+    // Exactly one part's worth stays a single put
+    let exact = payload(PART as usize, 3);
+    let key = format!("{}backups/exact.dump", prefix);
+    let chunks = stream::iter(vec![Ok(Bytes::from(exact.clone()))]).boxed();
+    let meta = uploader.upload(&key, chunks, &PutOptions::new()).await.unwrap();
+    assert_eq!(meta.checksum, Some(Checksum::of(&exact)));
+
+    // A failing source aborts the upload and leaves nothing behind
+    let key = format!("{}backups/failed.dump", prefix);
+    let failing = stream::iter(vec![
+        Ok(Bytes::from(payload(PART as usize, 1))),
+        Ok(Bytes::from(payload(PART as usize, 2))),
+        Err(StoreError::Io("source went away".into())),
+    ])
+    .boxed();
+    assert!(matches!(uploader.upload(&key, failing, &PutOptions::new()).await, Err(StoreError::Io(_))));
+    assert!(matches!(store.head(&key).await, Err(StoreError::NotFound(_))));
+}
+
+async fn resumed_upload(store: &Arc<dyn ObjectStore>, prefix: &str) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("artifact.bin");
+    let data = payload(2 * PART as usize + PART as usize / 2, 11);
+    tokio::fs::write(&path, &data).await.unwrap();
+    let uploader = Uploader::new(store.clone()).with_part_size(PART);
+    let key = format!("{}models/artifact.bin", prefix);
+    let options = PutOptions::new().with_content_type("application/octet-stream");
+    let upload = uploader.begin(&key, &options).await.unwrap();
+
+    // An earlier attempt got part 1 up intact and part 2 damaged before it was interrupted
+    let part = PART as usize;
+    store.upload_part(&upload, 1, Bytes::copy_from_slice(&data[..part])).await.unwrap();
+    let mut damaged = data[part..2 * part].to_vec();
+    damaged[17] ^= 0xff;
+    store.upload_part(&upload, 2, Bytes::from(damaged)).await.unwrap();
+
+    let resumed = uploader.resume_file(&upload, &path).await.unwrap();
+    assert_eq!(resumed.reused, [1]);
+    assert_eq!(resumed.uploaded, [2, 3]);
+    assert_eq!(resumed.meta.content_type.as_deref(), Some("application/octet-stream"));
+    assert!(read(store.as_ref(), &key).await.unwrap() == data);
+}
+
+async fn corrupted_part_rejected(store: &dyn ObjectStore, prefix: &str) {
+    let key = format!("{}backups/forged.dump", prefix);
+    let data = payload(PART as usize + 100, 5);
+    let upload = store.create_multipart(&key, PART, &PutOptions::new()).await.unwrap();
+    let first = store.upload_part(&upload, 1, Bytes::copy_from_slice(&data[..PART as usize])).await.unwrap();
+    let second = store.upload_part(&upload, 2, Bytes::copy_from_slice(&data[PART as usize..])).await.unwrap();
+
+    let forged = crate::store::UploadedPart { sha256: Checksum::of(b"something else").sha256, ..first.clone() };
+    let result = store.complete_multipart(&upload, &[forged, second.clone()]).await;
+    assert!(matches!(result, Err(StoreError::ChecksumMismatch(_))), "{:?}", result);
+    assert!(matches!(store.head(&key).await, Err(StoreError::NotFound(_))));
+
+    store.abort_multipart(&upload).await.unwrap();
+    store.abort_multipart(&upload).await.unwrap();
+}
+
+async fn presign(store: &dyn ObjectStore, prefix: &str) {
+    let key = format!("{}exports/shared.csv", prefix);
+    store.put(&key, Bytes::from_static(b"id\n"), &PutOptions::new()).await.unwrap();
+    for method in [PresignMethod::Get, PresignMethod::Put] {
+        match store.presign(&key, method, Duration::from_secs(300)).await {
+            Ok(url) => assert!(url.contains("shared.csv"), "{}", url),
+            Err(StoreError::Unsupported(_)) => {}
+            Err(e) => panic!("Presigning {:?} failed: {}", method, e),
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use sirsi_common::{ErrorKind, Retryable};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum StoreError {
+    #[error("Object not found: {0}")]
+    NotFound(String),
+
+    // Stored bytes don't match the digest recorded for them
+    #[error("Checksum mismatch: {0}")]
+    ChecksumMismatch(String),
+
+    #[error("Request throttled: {0}")]
+    Throttled(String),
+
+    #[error("Storage unavailable: {0}")]
+    Unavailable(String),
+
+    #[error("Access denied: {0}")]
+    AccessDenied(String),
+
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
+    #[error("Invalid request: {0}")]
+    Invalid(String),
+
+    #[error("Unsupported by this backend: {0}")]
+    Unsupported(String),
+
+    #[error("I/O error: {0}")]
+    Io(String),
+
+    #[error("Backend error: {0}")]
+    Backend(String),
+}
+
+impl StoreError {
+    pub fn from_kind(kind: ErrorKind, msg: impl Into<String>) -> Self {
+        let msg = msg.into();
+        match kind {
+            ErrorKind::Throttled => StoreError::Throttled(msg),
+            ErrorKind::ProviderOutage => StoreError::Unavailable(msg),
+            ErrorKind::AuthFailure => StoreError::AccessDenied(msg),
+            ErrorKind::NotFound => StoreError::NotFound(msg),
+            ErrorKind::Conflict => StoreError::Conflict(msg),
+            ErrorKind::InvalidInput => StoreError::Invalid(msg),
+            ErrorKind::Internal => StoreError::Backend(msg),
+        }
+    }
+}
+
+impl Retryable for StoreError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            StoreError::NotFound(_) => ErrorKind::NotFound,
+            StoreError::Throttled(_) => ErrorKind::Throttled,
+            StoreError::Unavailable(_) => ErrorKind::ProviderOutage,
+            StoreError::AccessDenied(_) => ErrorKind::AuthFailure,
+            StoreError::Conflict(_) => ErrorKind::Conflict,
+            StoreError::Invalid(_) | StoreError::Unsupported(_) => ErrorKind::InvalidInput,
+            StoreError::ChecksumMismatch(_) | StoreError::Io(_) | StoreError::Backend(_) => ErrorKind::Internal,
+        }
+    }
+}
+
+impl From<std::io::Error> for StoreError {
+    fn from(e: std::io::Error) -> Self {
+        match e.kind() {
+            std::io::ErrorKind::NotFound => StoreError::NotFound(e.to_string()),
+            _ => StoreError::Io(e.to_string()),
+        }
+    }
+}
+
+pub type StoreResult<T> = Result<T, StoreError>;
@@ -0,0 +1,312 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures::stream::StreamExt;
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use tokio_util::io::ReaderStream;
+
+use crate::checksum::{self, Checksum};
+use crate::error::{StoreError, StoreResult};
+use crate::store::{
+    validate_key, validate_part_number, ByteStream, MultipartUpload, ObjectMeta, ObjectStore, PresignMethod, PutOptions,
+    UploadedPart,
+};
+
+const PART_PREFIX: &str = "part-";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct StoredMeta {
+    content_type: Option<String>,
+    checksum: Option<Checksum>,
+    #[serde(default)]
+    metadata: HashMap<String, String>,
+}
+
+// Objects as plain files under `<root>/objects`, with their metadata beside them under
+// `<root>/meta` and multipart uploads staged under `<root>/uploads`
+pub struct LocalFileStore {
+    root: PathBuf,
+}
+
+impl LocalFileStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn object_path(&self, key: &str) -> PathBuf {
+        self.root.join("objects").join(key)
+    }
+
+    fn meta_path(&self, key: &str) -> PathBuf {
+        self.root.join("meta").join(format!("{}.json", key))
+    }
+
+    fn upload_dir(&self, upload: &MultipartUpload) -> StoreResult<PathBuf> {
+        if upload.upload_id.is_empty() || !upload.upload_id.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-') {
+            return Err(StoreError::Invalid(format!("Invalid upload id '{}'", upload.upload_id)));
+        }
+        Ok(self.root.join("uploads").join(&upload.upload_id))
+    }
+
+    // Written beside the destination and renamed over it, so readers never see a partial file
+    async fn write_atomic(&self, path: &Path, data: &[u8]) -> StoreResult<()> {
+        let partial = self.partial_path(path).await?;
+        if let Err(e) = tokio::fs::write(&partial, data).await {
+            let _ = tokio::fs::remove_file(&partial).await;
+            return Err(e.into());
+        }
+        self.commit(&partial, path).await
+    }
+
+    async fn partial_path(&self, path: &Path) -> StoreResult<PathBuf> {
+        let dir = path.parent().ok_or_else(|| StoreError::Invalid(format!("No parent for {}", path.display())))?;
+        tokio::fs::create_dir_all(dir).await?;
+        Ok(dir.join(format!(".partial-{}", uuid::Uuid::new_v4())))
+    }
+
+    async fn commit(&self, partial: &Path, path: &Path) -> StoreResult<()> {
+        if let Err(e) = tokio::fs::rename(partial, path).await {
+            let _ = tokio::fs::remove_file(partial).await;
+            return Err(e.into());
+        }
+        Ok(())
+    }
+
+    async fn write_meta(&self, key: &str, options: &PutOptions, checksum: Checksum) -> StoreResult<()> {
+        let meta = StoredMeta {
+            content_type: options.content_type.clone(),
+            checksum: Some(checksum),
+            metadata: options.metadata.clone(),
+        };
+        let json = serde_json::to_vec(&meta).map_err(|e| StoreError::Backend(e.to_string()))?;
+        self.write_atomic(&self.meta_path(key), &json).await
+    }
+
+    async fn read_meta(&self, key: &str) -> StoreResult<StoredMeta> {
+        match tokio::fs::read(self.meta_path(key)).await {
+            Ok(json) => serde_json::from_slice(&json)
+                .map_err(|e| StoreError::Backend(format!("Corrupt metadata for {}: {}", key, e))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(StoredMeta::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn stored_parts(&self, upload: &MultipartUpload) -> StoreResult<Vec<UploadedPart>> {
+        let dir = self.upload_dir(upload)?;
+        let mut entries = tokio::fs::read_dir(&dir)
+            .await
+            .map_err(|_| StoreError::NotFound(format!("No upload {} for {}", upload.upload_id, upload.key)))?;
+        let mut parts = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name();
+            let Some(number) = name.to_str().and_then(|n| n.strip_prefix(PART_PREFIX)).and_then(|n| n.parse().ok()) else {
+                continue;
+            };
+            let data = tokio::fs::read(entry.path()).await?;
+            parts.push(UploadedPart { number, size: data.len() as u64, sha256: Checksum::of(&data).sha256, etag: None });
+        }
+        parts.sort_by_key(|p| p.number);
+        Ok(parts)
+    }
+}
+
+fn missing(key: &str) -> impl FnOnce(std::io::Error) -> StoreError + '_ {
+    move |e| match e.kind() {
+        std::io::ErrorKind::NotFound => StoreError::NotFound(key.to_string()),
+        _ => e.into(),
+    }
+}
+
+#[async_trait]
+impl ObjectStore for LocalFileStore {
+    fn url(&self, key: &str) -> String {
+        format!("file://{}", self.object_path(key).display())
+    }
+
+    async fn put(&self, key: &str, data: Bytes, options: &PutOptions) -> StoreResult<ObjectMeta> {
+        validate_key(key)?;
+        self.write_atomic(&self.object_path(key), &data).await?;
+        self.write_meta(key, options, Checksum::of(&data)).await?;
+        self.head(key).await
+    }
+
+    async fn head(&self, key: &str) -> StoreResult<ObjectMeta> {
+        validate_key(key)?;
+        let stat = tokio::fs::metadata(self.object_path(key)).await.map_err(missing(key))?;
+        if !stat.is_file() {
+            return Err(StoreError::NotFound(key.to_string()));
+        }
+        let meta = self.read_meta(key).await?;
+        Ok(ObjectMeta {
+            key: key.to_string(),
+            size: stat.len(),
+            last_modified: stat.modified().ok().map(DateTime::<Utc>::from),
+            content_type: meta.content_type,
+            checksum: meta.checksum,
+            metadata: meta.metadata,
+        })
+    }
+
+    async fn get(&self, key: &str) -> StoreResult<(ObjectMeta, ByteStream)> {
+        let meta = self.head(key).await?;
+        let file = tokio::fs::File::open(self.object_path(key)).await.map_err(missing(key))?;
+        let stream = ReaderStream::new(file).map(|chunk| chunk.map_err(StoreError::from)).boxed();
+        let stream = checksum::verified(&meta, stream);
+        Ok((meta, stream))
+    }
+
+    async fn delete(&self, key: &str) -> StoreResult<()> {
+        validate_key(key)?;
+        for path in [self.object_path(key), self.meta_path(key)] {
+            match tokio::fs::remove_file(&path).await {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    async fn list(&self, prefix: &str) -> StoreResult<Vec<ObjectMeta>> {
+        let base = self.root.join("objects");
+        let mut keys = Vec::new();
+        let mut dirs = vec![base.clone()];
+        while let Some(dir) = dirs.pop() {
+            let mut entries = match tokio::fs::read_dir(&dir).await {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+            while let Some(entry) = entries.next_entry().await? {
+                let path = entry.path();
+                if entry.file_type().await?.is_dir() {
+                    dirs.push(path);
+                    continue;
+                }
+                let Ok(relative) = path.strip_prefix(&base) else { continue };
+                let key = relative.iter().map(|s| s.to_string_lossy()).collect::<Vec<_>>().join("/");
+                if key.starts_with(prefix) && !key.rsplit('/').next().unwrap_or_default().starts_with(".partial-") {
+                    keys.push(key);
+                }
+            }
+        }
+        keys.sort();
+        let mut objects = Vec::with_capacity(keys.len());
+        for key in keys {
+            match self.head(&key).await {
+                Ok(meta) => objects.push(meta),
+                // Deleted since the directory was read
+                Err(StoreError::NotFound(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(objects)
+    }
+
+    async fn presign(&self, _key: &str, _method: PresignMethod, _expires_in: Duration) -> StoreResult<String> {
+        Err(StoreError::Unsupported("Local files can't be shared through expiring links".into()))
+    }
+
+    async fn create_multipart(&self, key: &str, part_size: u64, options: &PutOptions) -> StoreResult<MultipartUpload> {
+        validate_key(key)?;
+        if part_size == 0 {
+            return Err(StoreError::Invalid("Part size must be positive".into()));
+        }
+        let upload = MultipartUpload {
+            key: key.to_string(),
+            upload_id: uuid::Uuid::new_v4().to_string(),
+            part_size,
+            options: options.clone(),
+        };
+        tokio::fs::create_dir_all(self.upload_dir(&upload)?).await?;
+        Ok(upload)
+    }
+
+    async fn upload_part(&self, upload: &MultipartUpload, number: u32, data: Bytes) -> StoreResult<UploadedPart> {
+        validate_part_number(number)?;
+        let dir = self.upload_dir(upload)?;
+        if !tokio::fs::try_exists(&dir).await? {
+            return Err(StoreError::NotFound(format!("No upload {} for {}", upload.upload_id, upload.key)));
+        }
+        self.write_atomic(&dir.join(format!("{}{:05}", PART_PREFIX, number)), &data).await?;
+        Ok(UploadedPart { number, size: data.len() as u64, sha256: Checksum::of(&data).sha256, etag: None })
+    }
+
+    async fn list_parts(&self, upload: &MultipartUpload) -> StoreResult<Vec<UploadedPart>> {
+        self.stored_parts(upload).await
+    }
+
+    async fn complete_multipart(&self, upload: &MultipartUpload, parts: &[UploadedPart]) -> StoreResult<ObjectMeta> {
+        let checksum = Checksum::multipart(upload, parts)?;
+        let stored = self.stored_parts(upload).await?;
+        for part in parts {
+            match stored.iter().find(|s| s.number == part.number) {
+                Some(s) if s.sha256 == part.sha256 && s.size == part.size => {}
+                Some(_) => {
+                    return Err(StoreError::ChecksumMismatch(format!(
+                        "Stored part {} of {} doesn't match its digest",
+                        part.number, upload.key
+                    )))
+                }
+                None => {
+                    return Err(StoreError::NotFound(format!("Part {} of {} was never uploaded", part.number, upload.key)))
+                }
+            }
+        }
+
+        let dir = self.upload_dir(upload)?;
+        let path = self.object_path(&upload.key);
+        let partial = self.partial_path(&path).await?;
+        let mut out = tokio::fs::File::create(&partial).await?;
+        for part in parts {
+            let mut file = tokio::fs::File::open(dir.join(format!("{}{:05}", PART_PREFIX, part.number))).await?;
+            if let Err(e) = tokio::io::copy(&mut file, &mut out).await {
+                let _ = tokio::fs::remove_file(&partial).await;
+                return Err(e.into());
+            }
+        }
+        out.flush().await?;
+        drop(out);
+        self.commit(&partial, &path).await?;
+        self.write_meta(&upload.key, &upload.options, checksum).await?;
+        tokio::fs::remove_dir_all(&dir).await?;
+        self.head(&upload.key).await
+    }
+
+    async fn abort_multipart(&self, upload: &MultipartUpload) -> StoreResult<()> {
+        match tokio::fs::remove_dir_all(self.upload_dir(upload)?).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conformance;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_conformance() {
+        let root = tempfile::tempdir().unwrap();
+        conformance::run(Arc::new(LocalFileStore::new(root.path()))).await;
+    }
+
+    #[tokio::test]
+    async fn test_corrupted_object_fails_the_read() {
+        let root = tempfile::tempdir().unwrap();
+        let store = LocalFileStore::new(root.path());
+        store.put("backups/db-orders.dump", Bytes::from_static(b"PGDMP orders"), &PutOptions::new()).await.unwrap();
+        std::fs::write(root.path().join("objects/backups/db-orders.dump"), b"PGDMP ordere").unwrap();
+
+        let (_, stream) = store.get("backups/db-orders.dump").await.unwrap();
+        let items: Vec<_> = stream.collect().await;
+        assert!(matches!(items.last(), Some(Err(StoreError::ChecksumMismatch(_)))));
+        assert!(matches!(store.get("backups/../../etc/passwd").await, Err(StoreError::Invalid(_))));
+    }
+}
@@ -0,0 +1,34 @@
+// Object storage shared by backups, artifacts and exports, with one trait over S3-compatible
+// stores, GCS, Azure Blob and the local filesystem
+pub mod checksum;
+pub mod error;
+pub mod fs;
+pub mod retry;
+pub mod store;
+pub mod upload;
+
+#[cfg(feature = "azure")]
+pub mod azure;
+#[cfg(feature = "gcs")]
+pub mod gcs;
+#[cfg(feature = "s3")]
+pub mod s3;
+#[cfg(any(feature = "gcs", feature = "azure"))]
+mod signing;
+
+#[cfg(test)]
+mod conformance;
+
+pub use checksum::Checksum;
+pub use error::{StoreError, StoreResult};
+pub use fs::LocalFileStore;
+pub use retry::RetryingStore;
+pub use store::{ByteStream, MultipartUpload, ObjectMeta, ObjectStore, PresignMethod, PutOptions, UploadedPart};
+pub use upload::{ResumedUpload, Uploader};
+
+#[cfg(feature = "azure")]
+pub use azure::{AzureBlobConfig, AzureBlobStore};
+#[cfg(feature = "gcs")]
+pub use gcs::{GcsConfig, GcsStore};
+#[cfg(feature = "s3")]
+pub use s3::S3Store;
@@ -0,0 +1,164 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use sirsi_common::{retry, RetryPolicy};
+
+use crate::error::StoreResult;
+use crate::store::{ByteStream, MultipartUpload, ObjectMeta, ObjectStore, PresignMethod, PutOptions, UploadedPart};
+
+// Retries throttled and unavailable responses the same way for every backend. Reads are retried
+// until the stream opens; a stream that fails midway is up to the caller to restart.
+pub struct RetryingStore {
+    inner: Arc<dyn ObjectStore>,
+    policy: RetryPolicy,
+}
+
+impl RetryingStore {
+    pub fn new(inner: Arc<dyn ObjectStore>) -> Self {
+        Self { inner, policy: RetryPolicy::default() }
+    }
+
+    pub fn with_policy(mut self, policy: RetryPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+}
+
+#[async_trait]
+impl ObjectStore for RetryingStore {
+    fn url(&self, key: &str) -> String {
+        self.inner.url(key)
+    }
+
+    async fn put(&self, key: &str, data: Bytes, options: &PutOptions) -> StoreResult<ObjectMeta> {
+        retry(&self.policy, || self.inner.put(key, data.clone(), options)).await
+    }
+
+    async fn head(&self, key: &str) -> StoreResult<ObjectMeta> {
+        retry(&self.policy, || self.inner.head(key)).await
+    }
+
+    async fn get(&self, key: &str) -> StoreResult<(ObjectMeta, ByteStream)> {
+        retry(&self.policy, || self.inner.get(key)).await
+    }
+
+    async fn delete(&self, key: &str) -> StoreResult<()> {
+        retry(&self.policy, || self.inner.delete(key)).await
+    }
+
+    async fn list(&self, prefix: &str) -> StoreResult<Vec<ObjectMeta>> {
+        retry(&self.policy, || self.inner.list(prefix)).await
+    }
+
+    async fn presign(&self, key: &str, method: PresignMethod, expires_in: Duration) -> StoreResult<String> {
+        retry(&self.policy, || self.inner.presign(key, method, expires_in)).await
+    }
+
+    async fn create_multipart(&self, key: &str, part_size: u64, options: &PutOptions) -> StoreResult<MultipartUpload> {
+        retry(&self.policy, || self.inner.create_multipart(key, part_size, options)).await
+    }
+
+    async fn upload_part(&self, upload: &MultipartUpload, number: u32, data: Bytes) -> StoreResult<UploadedPart> {
+        retry(&self.policy, || self.inner.upload_part(upload, number, data.clone())).await
+    }
+
+    async fn list_parts(&self, upload: &MultipartUpload) -> StoreResult<Vec<UploadedPart>> {
+        retry(&self.policy, || self.inner.list_parts(upload)).await
+    }
+
+    async fn complete_multipart(&self, upload: &MultipartUpload, parts: &[UploadedPart]) -> StoreResult<ObjectMeta> {
+        retry(&self.policy, || self.inner.complete_multipart(upload, parts)).await
+    }
+
+    async fn abort_multipart(&self, upload: &MultipartUpload) -> StoreResult<()> {
+        retry(&self.policy, || self.inner.abort_multipart(upload)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::StoreError;
+    use crate::fs::LocalFileStore;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    // Throttles the first two puts
+    struct Flaky {
+        inner: LocalFileStore,
+        puts: AtomicU32,
+    }
+
+    #[async_trait]
+    impl ObjectStore for Flaky {
+        fn url(&self, key: &str) -> String {
+            self.inner.url(key)
+        }
+
+        async fn put(&self, key: &str, data: Bytes, options: &PutOptions) -> StoreResult<ObjectMeta> {
+            if self.puts.fetch_add(1, Ordering::SeqCst) < 2 {
+                return Err(StoreError::Throttled("SlowDown".into()));
+            }
+            self.inner.put(key, data, options).await
+        }
+
+        async fn head(&self, key: &str) -> StoreResult<ObjectMeta> {
+            self.inner.head(key).await
+        }
+
+        async fn get(&self, key: &str) -> StoreResult<(ObjectMeta, ByteStream)> {
+            self.inner.get(key).await
+        }
+
+        async fn delete(&self, key: &str) -> StoreResult<()> {
+            self.inner.delete(key).await
+        }
+
+        async fn list(&self, prefix: &str) -> StoreResult<Vec<ObjectMeta>> {
+            self.inner.list(prefix).await
+        }
+
+        async fn presign(&self, key: &str, method: PresignMethod, expires_in: Duration) -> StoreResult<String> {
+            self.inner.presign(key, method, expires_in).await
+        }
+
+        async fn create_multipart(&self, key: &str, part_size: u64, options: &PutOptions) -> StoreResult<MultipartUpload> {
+            self.inner.create_multipart(key, part_size, options).await
+        }
+
+        async fn upload_part(&self, upload: &MultipartUpload, number: u32, data: Bytes) -> StoreResult<UploadedPart> {
+            self.inner.upload_part(upload, number, data).await
+        }
+
+        async fn list_parts(&self, upload: &MultipartUpload) -> StoreResult<Vec<UploadedPart>> {
+            self.inner.list_parts(upload).await
+        }
+
+        async fn complete_multipart(&self, upload: &MultipartUpload, parts: &[UploadedPart]) -> StoreResult<ObjectMeta> {
+            self.inner.complete_multipart(upload, parts).await
+        }
+
+        async fn abort_multipart(&self, upload: &MultipartUpload) -> StoreResult<()> {
+            self.inner.abort_multipart(upload).await
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retries_throttled_puts_but_not_missing_objects() {
+        let root = tempfile::tempdir().unwrap();
+        let flaky = Arc::new(Flaky { inner: LocalFileStore::new(root.path()), puts: AtomicU32::new(0) });
+        let store = RetryingStore::new(flaky.clone());
+
+        store.put("exports/a.csv", Bytes::from_static(b"id\n1\n"), &PutOptions::new()).await.unwrap();
+        assert_eq!(flaky.puts.load(Ordering::SeqCst), 3);
+
+        let single = RetryingStore::new(flaky.clone()).with_policy(RetryPolicy::new(1));
+        flaky.puts.store(0, Ordering::SeqCst);
+        assert!(matches!(
+            single.put("exports/b.csv", Bytes::new(), &PutOptions::new()).await,
+            Err(StoreError::Throttled(_))
+        ));
+        assert!(matches!(store.head("exports/missing.csv").await, Err(StoreError::NotFound(_))));
+    }
+}
@@ -0,0 +1,343 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use aws_sdk_s3::config::http::HttpResponse;
+use aws_sdk_s3::error::{DisplayErrorContext, ProvideErrorMetadata, SdkError};
+use aws_sdk_s3::presigning::PresigningConfig;
+use aws_sdk_s3::primitives::ByteStream as SdkByteStream;
+use aws_sdk_s3::types::{ChecksumAlgorithm, ChecksumMode, CompletedMultipartUpload, CompletedPart};
+use aws_sdk_s3::Client;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures::stream::StreamExt;
+use sirsi_common::ErrorKind;
+use tokio_util::io::ReaderStream;
+
+use crate::checksum::{self, Checksum, PART_SIZE_KEY};
+use crate::error::{StoreError, StoreResult};
+use crate::store::{
+    validate_key, validate_part_number, ByteStream, MultipartUpload, ObjectMeta, ObjectStore, PresignMethod, PutOptions,
+    UploadedPart,
+};
+
+// Any S3-compatible store reachable through the AWS SDK. Single puts and every part carry a
+// SHA-256 the service checks on receipt; multipart objects get S3's composite checksum, which
+// matches `Checksum::multipart`.
+pub struct S3Store {
+    client: Client,
+    bucket: String,
+}
+
+impl S3Store {
+    pub fn new(client: Client, bucket: impl Into<String>) -> Self {
+        Self { client, bucket: bucket.into() }
+    }
+}
+
+fn s3_error<E: ProvideErrorMetadata + std::error::Error + 'static>(action: &str, key: &str, e: SdkError<E, HttpResponse>) -> StoreError {
+    let status = e.raw_response().map(|r| r.status().as_u16());
+    let message = format!("Failed to {} {}: {}", action, key, DisplayErrorContext(&e));
+    match e.code() {
+        Some("BadDigest" | "InvalidDigest" | "InvalidPart" | "XAmzContentChecksumMismatch") => {
+            StoreError::ChecksumMismatch(message)
+        }
+        Some(code) => StoreError::from_kind(ErrorKind::from_aws_code(code), message),
+        // HEAD responses have no body to carry a code
+        None => match status {
+            Some(status) => StoreError::from_kind(ErrorKind::from_http_status(status), message),
+            None => StoreError::Unavailable(message),
+        },
+    }
+}
+
+fn to_chrono(time: Option<&aws_sdk_s3::primitives::DateTime>) -> Option<DateTime<Utc>> {
+    time.and_then(|t| DateTime::from_timestamp(t.secs(), t.subsec_nanos()))
+}
+
+fn b64_digest(sha256: &str) -> StoreResult<String> {
+    hex::decode(sha256).map(|d| BASE64.encode(d)).map_err(|_| StoreError::Invalid(format!("Malformed digest {}", sha256)))
+}
+
+// S3 reports multipart checksums as `<base64>-<parts>`; the part size comes from our own metadata
+fn native_checksum(native: Option<&str>, metadata: &mut HashMap<String, String>) -> Option<Checksum> {
+    let part_size = metadata.remove(PART_SIZE_KEY).and_then(|s| s.parse::<u64>().ok());
+    let (digest, parts) = match native?.split_once('-') {
+        Some((digest, parts)) => (digest, Some((parts.parse().ok()?, part_size.filter(|s| *s > 0)?))),
+        None => (native?, None),
+    };
+    Some(Checksum { sha256: hex::encode(BASE64.decode(digest).ok()?), parts })
+}
+
+#[async_trait]
+impl ObjectStore for S3Store {
+    fn url(&self, key: &str) -> String {
+        format!("s3://{}/{}", self.bucket, key)
+    }
+
+    async fn put(&self, key: &str, data: Bytes, options: &PutOptions) -> StoreResult<ObjectMeta> {
+        validate_key(key)?;
+        let checksum = Checksum::of(&data);
+        self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .set_content_type(options.content_type.clone())
+            .set_metadata(Some(options.metadata.clone()))
+            .checksum_sha256(b64_digest(&checksum.sha256)?)
+            .body(SdkByteStream::from(data))
+            .send()
+            .await
+            .map_err(|e| s3_error("upload", key, e))?;
+        self.head(key).await
+    }
+
+    async fn head(&self, key: &str) -> StoreResult<ObjectMeta> {
+        validate_key(key)?;
+        let out = self
+            .client
+            .head_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .checksum_mode(ChecksumMode::Enabled)
+            .send()
+            .await
+            .map_err(|e| s3_error("read", key, e))?;
+        let mut metadata = out.metadata().cloned().unwrap_or_default();
+        let checksum = native_checksum(out.checksum_sha256(), &mut metadata);
+        Ok(ObjectMeta {
+            key: key.to_string(),
+            size: out.content_length().unwrap_or_default().max(0) as u64,
+            last_modified: to_chrono(out.last_modified()),
+            content_type: out.content_type().map(str::to_string),
+            checksum,
+            metadata,
+        })
+    }
+
+    async fn get(&self, key: &str) -> StoreResult<(ObjectMeta, ByteStream)> {
+        let meta = self.head(key).await?;
+        let out = self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(|e| s3_error("read", key, e))?;
+        let stream = ReaderStream::new(out.body.into_async_read()).map(|chunk| chunk.map_err(StoreError::from)).boxed();
+        let stream = checksum::verified(&meta, stream);
+        Ok((meta, stream))
+    }
+
+    async fn delete(&self, key: &str) -> StoreResult<()> {
+        validate_key(key)?;
+        self.client
+            .delete_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(|e| s3_error("delete", key, e))?;
+        Ok(())
+    }
+
+    // Listings carry no metadata or checksums; `head` an object for those
+    async fn list(&self, prefix: &str) -> StoreResult<Vec<ObjectMeta>> {
+        let mut pages = self.client.list_objects_v2().bucket(&self.bucket).prefix(prefix).into_paginator().send();
+        let mut objects = Vec::new();
+        while let Some(page) = pages.next().await {
+            let page = page.map_err(|e| s3_error("list", prefix, e))?;
+            for object in page.contents() {
+                let Some(key) = object.key() else { continue };
+                objects.push(ObjectMeta {
+                    key: key.to_string(),
+                    size: object.size().unwrap_or_default().max(0) as u64,
+                    last_modified: to_chrono(object.last_modified()),
+                    content_type: None,
+                    checksum: None,
+                    metadata: HashMap::new(),
+                });
+            }
+        }
+        objects.sort_by(|a, b| a.key.cmp(&b.key));
+        Ok(objects)
+    }
+
+    async fn presign(&self, key: &str, method: PresignMethod, expires_in: Duration) -> StoreResult<String> {
+        validate_key(key)?;
+        let config = PresigningConfig::expires_in(expires_in)
+            .map_err(|e| StoreError::Invalid(format!("Invalid presign expiry: {}", e)))?;
+        let failed = |e: &dyn std::error::Error| StoreError::Backend(format!("Failed to presign {}: {}", key, DisplayErrorContext(e)));
+        let request = match method {
+            PresignMethod::Get => self.client.get_object().bucket(&self.bucket).key(key).presigned(config).await.map_err(|e| failed(&e))?,
+            PresignMethod::Put => self.client.put_object().bucket(&self.bucket).key(key).presigned(config).await.map_err(|e| failed(&e))?,
+        };
+        Ok(request.uri().to_string())
+    }
+
+    async fn create_multipart(&self, key: &str, part_size: u64, options: &PutOptions) -> StoreResult<MultipartUpload> {
+        validate_key(key)?;
+        if part_size == 0 {
+            return Err(StoreError::Invalid("Part size must be positive".into()));
+        }
+        let mut metadata = options.metadata.clone();
+        metadata.insert(PART_SIZE_KEY.to_string(), part_size.to_string());
+        let out = self
+            .client
+            .create_multipart_upload()
+            .bucket(&self.bucket)
+            .key(key)
+            .set_content_type(options.content_type.clone())
+            .set_metadata(Some(metadata))
+            .checksum_algorithm(ChecksumAlgorithm::Sha256)
+            .send()
+            .await
+            .map_err(|e| s3_error("start upload of", key, e))?;
+        let upload_id = out
+            .upload_id()
+            .ok_or_else(|| StoreError::Backend(format!("No upload id returned for {}", key)))?;
+        Ok(MultipartUpload {
+            key: key.to_string(),
+            upload_id: upload_id.to_string(),
+            part_size,
+            options: options.clone(),
+        })
+    }
+
+    async fn upload_part(&self, upload: &MultipartUpload, number: u32, data: Bytes) -> StoreResult<UploadedPart> {
+        validate_part_number(number)?;
+        let sha256 = Checksum::of(&data).sha256;
+        let size = data.len() as u64;
+        let out = self
+            .client
+            .upload_part()
+            .bucket(&self.bucket)
+            .key(&upload.key)
+            .upload_id(&upload.upload_id)
+            .part_number(number as i32)
+            .checksum_sha256(b64_digest(&sha256)?)
+            .body(SdkByteStream::from(data))
+            .send()
+            .await
+            .map_err(|e| s3_error("upload part of", &upload.key, e))?;
+        Ok(UploadedPart { number, size, sha256, etag: out.e_tag().map(str::to_string) })
+    }
+
+    async fn list_parts(&self, upload: &MultipartUpload) -> StoreResult<Vec<UploadedPart>> {
+        let mut parts = Vec::new();
+        let mut marker: Option<String> = None;
+        loop {
+            let out = self
+                .client
+                .list_parts()
+                .bucket(&self.bucket)
+                .key(&upload.key)
+                .upload_id(&upload.upload_id)
+                .set_part_number_marker(marker.take())
+                .send()
+                .await
+                .map_err(|e| s3_error("list parts of", &upload.key, e))?;
+            for part in out.parts() {
+                let (Some(number), Some(digest)) = (part.part_number(), part.checksum_sha256()) else { continue };
+                let Ok(digest) = BASE64.decode(digest) else { continue };
+                parts.push(UploadedPart {
+                    number: number.max(0) as u32,
+                    size: part.size().unwrap_or_default().max(0) as u64,
+                    sha256: hex::encode(digest),
+                    etag: part.e_tag().map(str::to_string),
+                });
+            }
+            if !out.is_truncated().unwrap_or(false) {
+                break;
+            }
+            marker = out.next_part_number_marker().map(str::to_string);
+            if marker.is_none() {
+                break;
+            }
+        }
+        parts.sort_by_key(|p| p.number);
+        Ok(parts)
+    }
+
+    async fn complete_multipart(&self, upload: &MultipartUpload, parts: &[UploadedPart]) -> StoreResult<ObjectMeta> {
+        Checksum::multipart(upload, parts)?;
+        let mut completed = Vec::with_capacity(parts.len());
+        for part in parts {
+            completed.push(
+                CompletedPart::builder()
+                    .part_number(part.number as i32)
+                    .set_e_tag(part.etag.clone())
+                    .checksum_sha256(b64_digest(&part.sha256)?)
+                    .build(),
+            );
+        }
+        self.client
+            .complete_multipart_upload()
+            .bucket(&self.bucket)
+            .key(&upload.key)
+            .upload_id(&upload.upload_id)
+            .multipart_upload(CompletedMultipartUpload::builder().set_parts(Some(completed)).build())
+            .send()
+            .await
+            .map_err(|e| s3_error("complete upload of", &upload.key, e))?;
+        self.head(&upload.key).await
+    }
+
+    async fn abort_multipart(&self, upload: &MultipartUpload) -> StoreResult<()> {
+        let result = self
+            .client
+            .abort_multipart_upload()
+            .bucket(&self.bucket)
+            .key(&upload.key)
+            .upload_id(&upload.upload_id)
+            .send()
+            .await
+            .map_err(|e| s3_error("abort upload of", &upload.key, e));
+        match result {
+            Err(StoreError::NotFound(_)) | Ok(_) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_native_multipart_checksum() {
+        let digest = BASE64.encode([7u8; 32]);
+        let mut metadata = HashMap::from([(PART_SIZE_KEY.to_string(), "5242880".to_string())]);
+        let checksum = native_checksum(Some(&format!("{}-3", digest)), &mut metadata).unwrap();
+        assert_eq!(checksum, Checksum { sha256: "07".repeat(32), parts: Some((3, 5_242_880)) });
+        assert!(metadata.is_empty());
+        assert_eq!(native_checksum(Some(&digest), &mut HashMap::new()).unwrap().parts, None);
+    }
+
+    // Needs LocalStack, e.g. `docker run -p 4566:4566 localstack/localstack`, at
+    // SIRSI_TEST_S3_ENDPOINT (default http://localhost:4566)
+    #[cfg(feature = "localstack-tests")]
+    #[tokio::test]
+    async fn test_conformance() {
+        use aws_sdk_s3::config::{BehaviorVersion, Credentials, Region};
+        use std::sync::Arc;
+
+        let endpoint = std::env::var("SIRSI_TEST_S3_ENDPOINT").unwrap_or_else(|_| "http://localhost:4566".into());
+        let config = aws_sdk_s3::Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .endpoint_url(endpoint)
+            .region(Region::new("us-east-1"))
+            .credentials_provider(Credentials::new("test", "test", None, None, "localstack"))
+            .force_path_style(true)
+            .build();
+        let client = Client::from_conf(config);
+        let bucket = format!("sirsi-conformance-{}", &uuid::Uuid::new_v4().simple().to_string()[..8]);
+        client.create_bucket().bucket(&bucket).send().await.unwrap();
+
+        crate::conformance::run(Arc::new(S3Store::new(client.clone(), &bucket))).await;
+        client.delete_bucket().bucket(&bucket).send().await.unwrap();
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures::stream::BoxStream;
+use serde::{Deserialize, Serialize};
+
+use crate::checksum::Checksum;
+use crate::error::{StoreError, StoreResult};
+
+pub type ByteStream = BoxStream<'static, StoreResult<Bytes>>;
+
+// Multipart uploads and their parts live under this prefix on backends without native multipart
+// support; `list` never returns keys below it
+pub const UPLOADS_PREFIX: &str = ".sirsi-uploads/";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ObjectMeta {
+    pub key: String,
+    pub size: u64,
+    pub last_modified: Option<DateTime<Utc>>,
+    pub content_type: Option<String>,
+    // None for objects written by something other than this crate; `list` may omit it too
+    pub checksum: Option<Checksum>,
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PutOptions {
+    pub content_type: Option<String>,
+    // User metadata; keys should be lowercase identifiers so every backend accepts them
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+}
+
+impl PutOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_content_type(mut self, content_type: impl Into<String>) -> Self {
+        self.content_type = Some(content_type.into());
+        self
+    }
+
+    pub fn with_metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PresignMethod {
+    Get,
+    Put,
+}
+
+// Handle to an upload in progress; persist it to resume after a restart
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MultipartUpload {
+    pub key: String,
+    pub upload_id: String,
+    // Every part but the last has exactly this size
+    pub part_size: u64,
+    pub options: PutOptions,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UploadedPart {
+    // 1-based
+    pub number: u32,
+    pub size: u64,
+    // Hex SHA-256 of the part's bytes
+    pub sha256: String,
+    // Backend token needed to complete the upload, where there is one
+    pub etag: Option<String>,
+}
+
+// Common surface over S3-compatible stores, GCS, Azure Blob and the local filesystem.
+// Implementations record a `Checksum` with everything they write and verify it while `get`
+// streams the object back, failing the stream's last item on a mismatch.
+#[async_trait]
+pub trait ObjectStore: Send + Sync {
+    // `s3://bucket/key` style location of an object, for records that outlive the store handle
+    fn url(&self, key: &str) -> String;
+
+    async fn put(&self, key: &str, data: Bytes, options: &PutOptions) -> StoreResult<ObjectMeta>;
+    async fn head(&self, key: &str) -> StoreResult<ObjectMeta>;
+    async fn get(&self, key: &str) -> StoreResult<(ObjectMeta, ByteStream)>;
+    // Deleting a missing object succeeds
+    async fn delete(&self, key: &str) -> StoreResult<()>;
+    // Sorted by key
+    async fn list(&self, prefix: &str) -> StoreResult<Vec<ObjectMeta>>;
+    async fn presign(&self, key: &str, method: PresignMethod, expires_in: Duration) -> StoreResult<String>;
+
+    async fn create_multipart(&self, key: &str, part_size: u64, options: &PutOptions) -> StoreResult<MultipartUpload>;
+    // Re-uploading a part number replaces it
+    async fn upload_part(&self, upload: &MultipartUpload, number: u32, data: Bytes) -> StoreResult<UploadedPart>;
+    // Parts uploaded so far; a number may be listed more than once where a backend keeps
+    // superseded attempts around until completion
+    async fn list_parts(&self, upload: &MultipartUpload) -> StoreResult<Vec<UploadedPart>>;
+    // Fails with `ChecksumMismatch` when a stored part doesn't match the digest given for it
+    async fn complete_multipart(&self, upload: &MultipartUpload, parts: &[UploadedPart]) -> StoreResult<ObjectMeta>;
+    async fn abort_multipart(&self, upload: &MultipartUpload) -> StoreResult<()>;
+}
+
+// Keys are relative, `/`-separated paths; empty segments and `.`/`..` are rejected everywhere so
+// a key means the same object on every backend
+pub fn validate_key(key: &str) -> StoreResult<()> {
+    if key.is_empty() || key.starts_with('/') || key.split('/').any(|s| s.is_empty() || s == "." || s == "..") {
+        return Err(StoreError::Invalid(format!("Invalid object key '{}'", key)));
+    }
+    if key.starts_with(UPLOADS_PREFIX) {
+        return Err(StoreError::Invalid(format!("Object keys may not start with {}", UPLOADS_PREFIX)));
+    }
+    Ok(())
+}
+
+pub(crate) fn validate_part_number(number: u32) -> StoreResult<()> {
+    if !(1..=10_000).contains(&number) {
+        return Err(StoreError::Invalid(format!("Part number {} is outside 1..=10000", number)));
+    }
+    Ok(())
+}
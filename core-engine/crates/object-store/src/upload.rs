@@ -0,0 +1,192 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use bytes::{Bytes, BytesMut};
+use futures::stream::{BoxStream, StreamExt};
+use tokio::io::AsyncReadExt;
+use tracing::{info, warn};
+
+use crate::checksum::Checksum;
+use crate::error::{StoreError, StoreResult};
+use crate::store::{MultipartUpload, ObjectMeta, ObjectStore, PutOptions, UploadedPart};
+
+pub const DEFAULT_PART_SIZE: u64 = 16 * 1024 * 1024;
+// S3 rejects smaller parts other than the last
+pub const MIN_PART_SIZE: u64 = 5 * 1024 * 1024;
+
+// What resuming an upload had to send
+#[derive(Debug, Clone)]
+pub struct ResumedUpload {
+    pub meta: ObjectMeta,
+    // Parts already stored with a matching digest
+    pub reused: Vec<u32>,
+    pub uploaded: Vec<u32>,
+}
+
+// Streams objects of any size into a store holding at most one part in memory. Objects that fit
+// in a single part go up with one `put`, anything larger as a multipart upload whose composed
+// checksum is checked against the store's before the upload counts as done.
+pub struct Uploader {
+    store: Arc<dyn ObjectStore>,
+    part_size: u64,
+}
+
+impl Uploader {
+    pub fn new(store: Arc<dyn ObjectStore>) -> Self {
+        Self { store, part_size: DEFAULT_PART_SIZE }
+    }
+
+    pub fn with_part_size(mut self, part_size: u64) -> Self {
+        self.part_size = part_size.max(MIN_PART_SIZE);
+        self
+    }
+
+    pub fn part_size(&self) -> u64 {
+        self.part_size
+    }
+
+    // Aborts the multipart upload if the stream or a part fails
+    pub async fn upload(&self, key: &str, mut chunks: BoxStream<'_, StoreResult<Bytes>>, options: &PutOptions) -> StoreResult<ObjectMeta> {
+        let part_size = self.part_size as usize;
+        let mut buffer = BytesMut::with_capacity(part_size);
+        let mut upload: Option<MultipartUpload> = None;
+        let mut parts = Vec::new();
+
+        let result = async {
+            while let Some(chunk) = chunks.next().await {
+                let mut chunk = chunk?;
+                while !chunk.is_empty() {
+                    let take = (part_size - buffer.len()).min(chunk.len());
+                    buffer.extend_from_slice(&chunk.split_to(take));
+                    if buffer.len() < part_size {
+                        continue;
+                    }
+                    // A full buffer only becomes a part once more data shows up, so an object of
+                    // exactly one part still goes up with a single put
+                    if upload.is_none() && chunk.is_empty() {
+                        break;
+                    }
+                    if upload.is_none() {
+                        upload = Some(self.store.create_multipart(key, self.part_size, options).await?);
+                    }
+                    if let Some(current) = &upload {
+                        let data = buffer.split().freeze();
+                        parts.push(self.store.upload_part(current, parts.len() as u32 + 1, data).await?);
+                    }
+                }
+            }
+            match &upload {
+                None => self.store.put(key, buffer.split().freeze(), options).await,
+                Some(current) => {
+                    if !buffer.is_empty() {
+                        let data = buffer.split().freeze();
+                        parts.push(self.store.upload_part(current, parts.len() as u32 + 1, data).await?);
+                    }
+                    self.complete(current, &parts).await
+                }
+            }
+        }
+        .await;
+
+        if let (Err(e), Some(upload)) = (&result, &upload) {
+            warn!("Aborting upload of {} after {} parts: {}", key, parts.len(), e);
+            if let Err(abort) = self.store.abort_multipart(upload).await {
+                warn!("Failed to abort upload {} of {}: {}", upload.upload_id, key, abort);
+            }
+        }
+        result
+    }
+
+    // Aborts the multipart upload on failure; use `begin` and `resume_file` to keep it for a retry
+    pub async fn upload_file(&self, key: &str, path: &Path, options: &PutOptions) -> StoreResult<ObjectMeta> {
+        let size = tokio::fs::metadata(path).await?.len();
+        if size <= self.part_size {
+            let data = tokio::fs::read(path).await?;
+            return self.store.put(key, Bytes::from(data), options).await;
+        }
+        let upload = self.begin(key, options).await?;
+        match self.resume_file(&upload, path).await {
+            Ok(resumed) => Ok(resumed.meta),
+            Err(e) => {
+                if let Err(abort) = self.store.abort_multipart(&upload).await {
+                    warn!("Failed to abort upload {} of {}: {}", upload.upload_id, key, abort);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    pub async fn begin(&self, key: &str, options: &PutOptions) -> StoreResult<MultipartUpload> {
+        self.store.create_multipart(key, self.part_size, options).await
+    }
+
+    // Sends the parts of `path` the store doesn't already hold with a matching digest, then
+    // completes the upload. Safe to call again after any failure, including a failed completion.
+    pub async fn resume_file(&self, upload: &MultipartUpload, path: &Path) -> StoreResult<ResumedUpload> {
+        let stored = self.store.list_parts(upload).await?;
+        let mut file = tokio::fs::File::open(path).await?;
+        let mut parts = Vec::new();
+        let mut reused = Vec::new();
+        let mut uploaded = Vec::new();
+        let mut buffer = vec![0u8; upload.part_size as usize];
+        loop {
+            let read = read_full(&mut file, &mut buffer).await?;
+            if read == 0 && !parts.is_empty() {
+                break;
+            }
+            let number = parts.len() as u32 + 1;
+            let data = &buffer[..read];
+            let sha256 = Checksum::of(data).sha256;
+            let existing = stored
+                .iter()
+                .rev()
+                .find(|p| p.number == number && p.size == read as u64 && p.sha256 == sha256);
+            match existing {
+                Some(part) => {
+                    parts.push(part.clone());
+                    reused.push(number);
+                }
+                None => {
+                    parts.push(self.store.upload_part(upload, number, Bytes::copy_from_slice(data)).await?);
+                    uploaded.push(number);
+                }
+            }
+            if read < buffer.len() {
+                break;
+            }
+        }
+        let meta = self.complete(upload, &parts).await?;
+        info!(
+            "Uploaded {} to {} ({} parts sent, {} reused)",
+            path.display(),
+            upload.key,
+            uploaded.len(),
+            reused.len()
+        );
+        Ok(ResumedUpload { meta, reused, uploaded })
+    }
+
+    async fn complete(&self, upload: &MultipartUpload, parts: &[UploadedPart]) -> StoreResult<ObjectMeta> {
+        let expected = Checksum::multipart(upload, parts)?;
+        let meta = self.store.complete_multipart(upload, parts).await?;
+        match &meta.checksum {
+            Some(actual) if actual != &expected => Err(StoreError::ChecksumMismatch(format!(
+                "{} was stored as {}, expected {}",
+                upload.key, actual, expected
+            ))),
+            _ => Ok(meta),
+        }
+    }
+}
+
+async fn read_full(file: &mut tokio::fs::File, buffer: &mut [u8]) -> StoreResult<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        let read = file.read(&mut buffer[filled..]).await?;
+        if read == 0 {
+            break;
+        }
+        filled += read;
+    }
+    Ok(filled)
+}
@@ -0,0 +1,136 @@
+use sirsi_common::{ErrorKind, Retryable};
+use thiserror::Error;
+use tonic::Status;
+
+#[derive(Error, Debug)]
+pub enum ObservabilityError {
+    #[error("Metrics error: {0}")]
+    Metrics(String),
+
+    #[error("Tracing error: {0}")]
+    Tracing(String),
+
+    #[error("Alert error: {0}")]
+    Alert(String),
+
+    #[error("Storage error: {0}")]
+    Storage(String),
+
+    #[error("Provider error: {0}")]
+    Provider(String),
+
+    #[error("Request throttled: {0}")]
+    Throttled(String),
+
+    #[error("Provider unavailable: {0}")]
+    Unavailable(String),
+
+    #[error("Authentication error: {0}")]
+    Auth(String),
+
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
+    #[error("Validation error: {0}")]
+    Validation(String),
+
+    #[error("Resource not found: {0}")]
+    NotFound(String),
+
+    #[error("Configuration error: {0}")]
+    Config(String),
+
+    #[error("Internal error: {0}")]
+    Internal(String),
+}
+
+impl ObservabilityError {
+    pub fn from_kind(kind: ErrorKind, msg: impl Into<String>) -> Self {
+        let msg = msg.into();
+        match kind {
+            ErrorKind::Throttled => ObservabilityError::Throttled(msg),
+            ErrorKind::ProviderOutage => ObservabilityError::Unavailable(msg),
+            ErrorKind::AuthFailure => ObservabilityError::Auth(msg),
+            ErrorKind::NotFound => ObservabilityError::NotFound(msg),
+            ErrorKind::Conflict => ObservabilityError::Conflict(msg),
+            ErrorKind::InvalidInput => ObservabilityError::Validation(msg),
+            ErrorKind::Internal => ObservabilityError::Provider(msg),
+        }
+    }
+
+    pub fn from_aws_code(code: Option<&str>, msg: impl Into<String>) -> Self {
+        let kind = code.map(ErrorKind::from_aws_code).unwrap_or(ErrorKind::Internal);
+        Self::from_kind(kind, msg)
+    }
+
+    pub fn from_gcp_status(http_code: Option<u16>, status: Option<&str>, msg: impl Into<String>) -> Self {
+        Self::from_kind(ErrorKind::from_gcp_status(http_code, status), msg)
+    }
+
+    pub fn from_http_status(status: u16, msg: impl Into<String>) -> Self {
+        Self::from_kind(ErrorKind::from_http_status(status), msg)
+    }
+}
+
+impl Retryable for ObservabilityError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            ObservabilityError::Metrics(_) => ErrorKind::Internal,
+            ObservabilityError::Tracing(_) => ErrorKind::Internal,
+            ObservabilityError::Alert(_) => ErrorKind::Internal,
+            ObservabilityError::Storage(_) => ErrorKind::ProviderOutage,
+            ObservabilityError::Provider(_) => ErrorKind::Internal,
+            ObservabilityError::Throttled(_) => ErrorKind::Throttled,
+            ObservabilityError::Unavailable(_) => ErrorKind::ProviderOutage,
+            ObservabilityError::Auth(_) => ErrorKind::AuthFailure,
+            ObservabilityError::Conflict(_) => ErrorKind::Conflict,
+            ObservabilityError::Validation(_) => ErrorKind::InvalidInput,
+            ObservabilityError::NotFound(_) => ErrorKind::NotFound,
+            ObservabilityError::Config(_) => ErrorKind::InvalidInput,
+            ObservabilityError::Internal(_) => ErrorKind::Internal,
+        }
+    }
+}
+
+impl From<ObservabilityError> for Status {
+    fn from(error: ObservabilityError) -> Self {
+        match error {
+            ObservabilityError::Metrics(msg) => Status::internal(msg),
+            ObservabilityError::Tracing(msg) => Status::internal(msg),
+            ObservabilityError::Alert(msg) => Status::internal(msg),
+            ObservabilityError::Storage(msg) => Status::unavailable(msg),
+            ObservabilityError::Provider(msg) => Status::internal(msg),
+            ObservabilityError::Throttled(msg) => Status::resource_exhausted(msg),
+            ObservabilityError::Unavailable(msg) => Status::unavailable(msg),
+            ObservabilityError::Auth(msg) => Status::unauthenticated(msg),
+            ObservabilityError::Conflict(msg) => Status::aborted(msg),
+            ObservabilityError::Validation(msg) => Status::invalid_argument(msg),
+            ObservabilityError::NotFound(msg) => Status::not_found(msg),
+            ObservabilityError::Config(msg) => Status::failed_precondition(msg),
+            ObservabilityError::Internal(msg) => Status::internal(msg),
+        }
+    }
+}
+
+pub type ObservabilityResult<T> = Result<T, ObservabilityError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_provider_errors_classify() {
+        let err = ObservabilityError::from_aws_code(Some("ResourceNotFoundException"), "Failed to get log events");
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+
+        let err = ObservabilityError::from_aws_code(Some("ThrottlingException"), "Failed to put metric data");
+        assert!(err.is_retryable());
+
+        let err = ObservabilityError::from_http_status(503, "Exporter endpoint unavailable");
+        assert!(matches!(err, ObservabilityError::Unavailable(_)));
+        assert!(err.is_retryable());
+
+        let err = ObservabilityError::from_http_status(401, "Invalid API token");
+        assert_eq!(err.kind(), ErrorKind::AuthFailure);
+    }
+}
@@ -0,0 +1,6 @@
+pub mod error;
+pub mod logs;
+pub mod monitoring;
+pub mod tracing;
+
+pub use error::{ObservabilityError, ObservabilityResult};
@@ -0,0 +1,283 @@
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Bound;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use tokio::sync::RwLock;
+
+use super::{LogPage, LogQuery, LogRecord, LogsManager};
+use crate::error::{ObservabilityError, ObservabilityResult};
+
+// Records are keyed by timestamp plus an ingest sequence so equal timestamps keep a
+// stable order and page tokens stay valid while new logs arrive
+type LogKey = (i64, u64);
+
+#[derive(Default)]
+struct LogIndex {
+    records: BTreeMap<LogKey, LogRecord>,
+    by_trace: HashMap<String, Vec<LogKey>>,
+    next_seq: u64,
+}
+
+pub struct InMemoryLogsManager {
+    index: RwLock<LogIndex>,
+    retention: Duration,
+}
+
+impl Default for InMemoryLogsManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InMemoryLogsManager {
+    pub fn new() -> Self {
+        Self {
+            index: RwLock::new(LogIndex::default()),
+            retention: Duration::from_secs(7 * 24 * 3600),
+        }
+    }
+
+    pub fn with_retention(mut self, retention: Duration) -> Self {
+        self.retention = retention;
+        self
+    }
+}
+
+fn timestamp_key(at: DateTime<Utc>) -> i64 {
+    at.timestamp_nanos_opt().unwrap_or(if at.timestamp() < 0 { i64::MIN } else { i64::MAX })
+}
+
+fn encode_token(key: LogKey) -> String {
+    format!("{}-{}", key.0, key.1)
+}
+
+fn decode_token(token: &str) -> ObservabilityResult<LogKey> {
+    let invalid = || ObservabilityError::Validation(format!("Invalid page token {}", token));
+    let (nanos, seq) = token.rsplit_once('-').ok_or_else(invalid)?;
+    Ok((nanos.parse().map_err(|_| invalid())?, seq.parse().map_err(|_| invalid())?))
+}
+
+#[async_trait]
+impl LogsManager for InMemoryLogsManager {
+    async fn ingest(&self, records: Vec<LogRecord>) -> ObservabilityResult<usize> {
+        // Validate the whole batch first so a bad record doesn't leave it half-ingested
+        let records = records
+            .into_iter()
+            .map(LogRecord::normalize)
+            .collect::<ObservabilityResult<Vec<_>>>()?;
+        let count = records.len();
+        let mut index = self.index.write().await;
+        for record in records {
+            let key = (timestamp_key(record.timestamp), index.next_seq);
+            index.next_seq += 1;
+            if let Some(trace_id) = &record.trace_id {
+                index.by_trace.entry(trace_id.clone()).or_default().push(key);
+            }
+            index.records.insert(key, record);
+        }
+        Ok(count)
+    }
+
+    async fn query(&self, query: LogQuery) -> ObservabilityResult<LogPage> {
+        let after = query.page_token.as_deref().map(decode_token).transpose()?;
+        let size = query.page_size();
+        let index = self.index.read().await;
+
+        let lower = query.start_time.map(|start| (timestamp_key(start), 0));
+        let upper = query.end_time.map(|end| (timestamp_key(end), 0));
+        let (mut low, mut high) = (
+            lower.map_or(Bound::Unbounded, Bound::Included),
+            upper.map_or(Bound::Unbounded, Bound::Excluded),
+        );
+        match after {
+            Some(key) if query.newest_first => high = Bound::Excluded(key),
+            Some(key) => low = Bound::Excluded(key),
+            None => {}
+        }
+        // BTreeMap::range panics on inverted bounds
+        if let (Bound::Included(l) | Bound::Excluded(l), Bound::Excluded(h)) = (low, high) {
+            if l >= h {
+                return Ok(LogPage { records: Vec::new(), next_page_token: None });
+            }
+        }
+
+        let range = index.records.range((low, high));
+        let matching: Box<dyn Iterator<Item = (&LogKey, &LogRecord)>> = if query.newest_first {
+            Box::new(range.rev().filter(|(_, r)| query.matches(r)))
+        } else {
+            Box::new(range.filter(|(_, r)| query.matches(r)))
+        };
+        // One extra record tells us whether another page exists
+        let mut page: Vec<(LogKey, LogRecord)> = matching.take(size + 1).map(|(k, r)| (*k, r.clone())).collect();
+        let next_page_token = if page.len() > size {
+            page.truncate(size);
+            page.last().map(|(key, _)| encode_token(*key))
+        } else {
+            None
+        };
+        Ok(LogPage {
+            records: page.into_iter().map(|(_, r)| r).collect(),
+            next_page_token,
+        })
+    }
+
+    async fn logs_for_trace(&self, trace_id: &str) -> ObservabilityResult<Vec<LogRecord>> {
+        let index = self.index.read().await;
+        let mut keys = index.by_trace.get(&trace_id.to_ascii_lowercase()).cloned().unwrap_or_default();
+        keys.sort();
+        Ok(keys.iter().filter_map(|key| index.records.get(key).cloned()).collect())
+    }
+
+    async fn enforce_retention(&self, now: DateTime<Utc>) -> ObservabilityResult<usize> {
+        let retention = chrono::Duration::from_std(self.retention).unwrap_or(chrono::Duration::MAX);
+        let Some(cutoff) = now.checked_sub_signed(retention) else {
+            return Ok(0);
+        };
+        let mut index = self.index.write().await;
+        let kept = index.records.split_off(&(timestamp_key(cutoff), 0));
+        let expired = std::mem::replace(&mut index.records, kept);
+        for (key, record) in &expired {
+            if let Some(trace_id) = &record.trace_id {
+                if let Some(keys) = index.by_trace.get_mut(trace_id) {
+                    keys.retain(|k| k != key);
+                    if keys.is_empty() {
+                        index.by_trace.remove(trace_id);
+                    }
+                }
+            }
+        }
+        Ok(expired.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logs::{correlate, LogSeverity};
+    use crate::tracing::{AttributeValue, Span, SpanKind, SpanStatus, Trace, TraceStatus};
+    use chrono::TimeZone;
+
+    const TRACE: &str = "4BF92F3577B34DA6A3CE929D0E0E4736";
+
+    fn at(second: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, second).unwrap()
+    }
+
+    fn span(span_id: &str, name: &str) -> Span {
+        Span {
+            span_id: span_id.to_string(),
+            trace_id: TRACE.to_lowercase(),
+            parent_span_id: None,
+            name: name.to_string(),
+            kind: SpanKind::Server,
+            start_time: at(0),
+            end_time: at(10),
+            attributes: HashMap::new(),
+            events: Vec::new(),
+            links: Vec::new(),
+            status: SpanStatus::Ok,
+        }
+    }
+
+    async fn seeded() -> InMemoryLogsManager {
+        let logs = InMemoryLogsManager::new();
+        let service = |name: &str| AttributeValue::String(name.to_string());
+        logs.ingest(vec![
+            LogRecord::new(at(1), LogSeverity::Info, "request started")
+                .with_resource("service.name", service("api"))
+                .with_attribute("http.status_code", AttributeValue::Int(200))
+                .with_trace(TRACE, Some("00f067aa0ba902b7".to_string())),
+            LogRecord::new(at(2), LogSeverity::Error, "Database timeout after 5s")
+                .with_resource("service.name", service("orders"))
+                .with_attribute("http.status_code", AttributeValue::Int(500))
+                .with_trace(TRACE, Some("53995c3f42cd8ad8".to_string())),
+            LogRecord::new(at(3), LogSeverity::Warn, "database slow")
+                .with_resource("service.name", service("orders")),
+            LogRecord::new(at(4), LogSeverity::Info, "request finished")
+                .with_resource("service.name", service("api"))
+                .with_trace(TRACE, Some("00f067aa0ba902b7".to_string())),
+        ])
+        .await
+        .unwrap();
+        logs
+    }
+
+    #[tokio::test]
+    async fn test_query_filters_and_pagination() {
+        let logs = seeded().await;
+
+        let query = |attributes: &[(&str, &str)]| LogQuery {
+            attributes: attributes.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            ..LogQuery::default()
+        };
+        let orders = logs.query(query(&[("service.name", "orders")])).await.unwrap();
+        assert_eq!(orders.records.len(), 2);
+        let failed = logs
+            .query(query(&[("service.name", "orders"), ("http.status_code", "500")]))
+            .await
+            .unwrap();
+        assert_eq!(failed.records.len(), 1);
+        assert_eq!(failed.records[0].body, "Database timeout after 5s");
+
+        let text = LogQuery {
+            text: Some("DATABASE".to_string()),
+            min_severity: Some(LogSeverity::Warn),
+            start_time: Some(at(3)),
+            ..LogQuery::default()
+        };
+        let slow = logs.query(text).await.unwrap();
+        assert_eq!(slow.records.len(), 1);
+        assert_eq!(slow.records[0].body, "database slow");
+
+        let mut page_query = LogQuery { limit: Some(3), newest_first: true, ..LogQuery::default() };
+        let first = logs.query(page_query.clone()).await.unwrap();
+        assert_eq!(first.records.iter().map(|r| r.timestamp).collect::<Vec<_>>(), vec![at(4), at(3), at(2)]);
+        page_query.page_token = first.next_page_token;
+        let second = logs.query(page_query).await.unwrap();
+        assert_eq!(second.records.len(), 1);
+        assert_eq!(second.records[0].timestamp, at(1));
+        assert!(second.next_page_token.is_none());
+
+        assert!(logs
+            .ingest(vec![LogRecord::new(at(5), LogSeverity::Info, "bad").with_trace("xyz", None)])
+            .await
+            .is_err());
+        assert_eq!(logs.enforce_retention(at(3) + chrono::Duration::days(7)).await.unwrap(), 2);
+        assert_eq!(logs.logs_for_trace(TRACE).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_trace_join() {
+        let logs = seeded().await;
+        let trace = Trace {
+            trace_id: TRACE.to_string(),
+            name: "GET /orders".to_string(),
+            start_time: at(0),
+            end_time: at(10),
+            spans: vec![span("00f067aa0ba902b7", "GET /orders"), span("aaaaaaaaaaaaaaaa", "cache lookup")],
+            status: TraceStatus::Ok,
+            tags: HashMap::new(),
+        };
+
+        let trace_logs = logs.logs_for_trace(&trace.trace_id).await.unwrap();
+        assert_eq!(trace_logs.len(), 3);
+        let by_query = logs
+            .query(LogQuery { trace_id: Some(TRACE.to_string()), ..LogQuery::default() })
+            .await
+            .unwrap();
+        assert_eq!(by_query.records.len(), 3);
+
+        let groups = correlate(&trace, trace_logs);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].span_name.as_deref(), Some("GET /orders"));
+        assert_eq!(
+            groups[0].logs.iter().map(|r| r.body.as_str()).collect::<Vec<_>>(),
+            vec!["request started", "request finished"]
+        );
+        // The orders span wasn't part of the fetched trace but its log still surfaces
+        assert_eq!(groups[1].span_id.as_deref(), Some("53995c3f42cd8ad8"));
+        assert_eq!(groups[1].span_name, None);
+    }
+}
@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
+
+use crate::error::{ObservabilityError, ObservabilityResult};
+use crate::tracing::{AttributeValue, Trace};
+
+pub mod memory;
+pub mod otlp;
+
+pub use memory::InMemoryLogsManager;
+
+pub const DEFAULT_PAGE_SIZE: usize = 100;
+pub const MAX_PAGE_SIZE: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum LogSeverity {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+    Fatal,
+}
+
+impl LogSeverity {
+    // OpenTelemetry severity numbers: 1-4 trace, 5-8 debug, ... 21-24 fatal
+    pub fn from_number(number: i32) -> Option<Self> {
+        match number {
+            1..=4 => Some(LogSeverity::Trace),
+            5..=8 => Some(LogSeverity::Debug),
+            9..=12 => Some(LogSeverity::Info),
+            13..=16 => Some(LogSeverity::Warn),
+            17..=20 => Some(LogSeverity::Error),
+            21..=24 => Some(LogSeverity::Fatal),
+            _ => None,
+        }
+    }
+
+    pub fn from_text(text: &str) -> Option<Self> {
+        match text.to_ascii_lowercase().as_str() {
+            "trace" => Some(LogSeverity::Trace),
+            "debug" => Some(LogSeverity::Debug),
+            "info" | "information" => Some(LogSeverity::Info),
+            "warn" | "warning" => Some(LogSeverity::Warn),
+            "error" => Some(LogSeverity::Error),
+            "fatal" | "critical" => Some(LogSeverity::Fatal),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogRecord {
+    pub timestamp: DateTime<Utc>,
+    pub observed_timestamp: Option<DateTime<Utc>>,
+    pub severity: LogSeverity,
+    pub body: String,
+    pub attributes: HashMap<String, AttributeValue>,
+    pub resource: HashMap<String, AttributeValue>,
+    pub trace_id: Option<String>,
+    pub span_id: Option<String>,
+}
+
+impl LogRecord {
+    pub fn new(timestamp: DateTime<Utc>, severity: LogSeverity, body: impl Into<String>) -> Self {
+        Self {
+            timestamp,
+            observed_timestamp: None,
+            severity,
+            body: body.into(),
+            attributes: HashMap::new(),
+            resource: HashMap::new(),
+            trace_id: None,
+            span_id: None,
+        }
+    }
+
+    pub fn with_attribute(mut self, key: impl Into<String>, value: AttributeValue) -> Self {
+        self.attributes.insert(key.into(), value);
+        self
+    }
+
+    pub fn with_resource(mut self, key: impl Into<String>, value: AttributeValue) -> Self {
+        self.resource.insert(key.into(), value);
+        self
+    }
+
+    pub fn with_trace(mut self, trace_id: impl Into<String>, span_id: Option<String>) -> Self {
+        self.trace_id = Some(trace_id.into());
+        self.span_id = span_id;
+        self
+    }
+
+    // Trace and span ids are stored as lowercase hex so joins are exact
+    pub fn normalize(mut self) -> ObservabilityResult<Self> {
+        self.trace_id = normalize_id(self.trace_id.take(), 32, "trace")?;
+        self.span_id = normalize_id(self.span_id.take(), 16, "span")?;
+        if self.span_id.is_some() && self.trace_id.is_none() {
+            return Err(ObservabilityError::Validation("Log record has a span id but no trace id".to_string()));
+        }
+        Ok(self)
+    }
+
+    // Record attributes take precedence over resource attributes of the same name
+    pub fn attribute(&self, key: &str) -> Option<&AttributeValue> {
+        self.attributes.get(key).or_else(|| self.resource.get(key))
+    }
+}
+
+fn normalize_id(id: Option<String>, length: usize, kind: &str) -> ObservabilityResult<Option<String>> {
+    let Some(id) = id.filter(|id| !id.is_empty()) else {
+        return Ok(None);
+    };
+    if id.len() != length || !id.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(ObservabilityError::Validation(format!("Invalid {} id {}", kind, id)));
+    }
+    // OTLP uses all zeros for "no id"
+    if id.chars().all(|c| c == '0') {
+        return Ok(None);
+    }
+    Ok(Some(id.to_ascii_lowercase()))
+}
+
+pub fn attribute_string(value: &AttributeValue) -> String {
+    match value {
+        AttributeValue::String(s) => s.clone(),
+        AttributeValue::Int(i) => i.to_string(),
+        AttributeValue::Float(f) => f.to_string(),
+        AttributeValue::Bool(b) => b.to_string(),
+        AttributeValue::Array(values) => {
+            let items: Vec<String> = values.iter().map(attribute_string).collect();
+            format!("[{}]", items.join(","))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LogQuery {
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+    pub min_severity: Option<LogSeverity>,
+    // Every whitespace-separated term must appear in the body, ignoring case
+    pub text: Option<String>,
+    pub attributes: HashMap<String, String>,
+    pub trace_id: Option<String>,
+    pub limit: Option<usize>,
+    pub page_token: Option<String>,
+    pub newest_first: bool,
+}
+
+impl LogQuery {
+    pub fn matches(&self, record: &LogRecord) -> bool {
+        if self.start_time.is_some_and(|start| record.timestamp < start)
+            || self.end_time.is_some_and(|end| record.timestamp >= end)
+            || self.min_severity.is_some_and(|min| record.severity < min)
+        {
+            return false;
+        }
+        if let Some(trace_id) = &self.trace_id {
+            if record.trace_id.as_deref() != Some(trace_id.to_ascii_lowercase().as_str()) {
+                return false;
+            }
+        }
+        if let Some(text) = &self.text {
+            let body = record.body.to_lowercase();
+            if !text.split_whitespace().all(|term| body.contains(&term.to_lowercase())) {
+                return false;
+            }
+        }
+        self.attributes
+            .iter()
+            .all(|(key, expected)| record.attribute(key).is_some_and(|value| &attribute_string(value) == expected))
+    }
+
+    pub fn page_size(&self) -> usize {
+        self.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogPage {
+    pub records: Vec<LogRecord>,
+    // Pass back as `page_token` to continue after the last record of this page
+    pub next_page_token: Option<String>,
+}
+
+#[async_trait]
+pub trait LogsManager: Send + Sync {
+    async fn ingest(&self, records: Vec<LogRecord>) -> ObservabilityResult<usize>;
+    async fn query(&self, query: LogQuery) -> ObservabilityResult<LogPage>;
+    async fn logs_for_trace(&self, trace_id: &str) -> ObservabilityResult<Vec<LogRecord>>;
+    // Drops records older than the configured retention and returns how many were removed
+    async fn enforce_retention(&self, now: DateTime<Utc>) -> ObservabilityResult<usize>;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpanLogs {
+    pub span_id: Option<String>,
+    pub span_name: Option<String>,
+    pub logs: Vec<LogRecord>,
+}
+
+// Groups a trace's logs under its spans in span order; logs without a span in the trace
+// are grouped by their own span id at the end
+pub fn correlate(trace: &Trace, logs: Vec<LogRecord>) -> Vec<SpanLogs> {
+    let mut groups: Vec<SpanLogs> = trace
+        .spans
+        .iter()
+        .map(|span| SpanLogs {
+            span_id: Some(span.span_id.to_ascii_lowercase()),
+            span_name: Some(span.name.clone()),
+            logs: Vec::new(),
+        })
+        .collect();
+    let trace_id = trace.trace_id.to_ascii_lowercase();
+    for record in logs.into_iter().filter(|r| r.trace_id.as_deref() == Some(trace_id.as_str())) {
+        match groups.iter_mut().find(|g| g.span_id == record.span_id) {
+            Some(group) => group.logs.push(record),
+            None => groups.push(SpanLogs {
+                span_id: record.span_id.clone(),
+                span_name: None,
+                logs: vec![record],
+            }),
+        }
+    }
+    for group in &mut groups {
+        group.logs.sort_by_key(|r| r.timestamp);
+    }
+    groups.retain(|g| !g.logs.is_empty());
+    groups
+}
@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::{Json, Router};
+use chrono::{DateTime, TimeZone, Utc};
+use serde_json::{json, Value};
+use tracing::debug;
+
+use super::{LogRecord, LogSeverity, LogsManager};
+use crate::error::{ObservabilityError, ObservabilityResult};
+use crate::tracing::AttributeValue;
+
+pub const OTLP_LOGS_PATH: &str = "/v1/logs";
+
+fn invalid(msg: impl Into<String>) -> ObservabilityError {
+    ObservabilityError::Validation(format!("Invalid OTLP logs payload: {}", msg.into()))
+}
+
+// proto3 JSON renders 64-bit integers as strings
+fn int64(value: &Value) -> Option<i64> {
+    value.as_i64().or_else(|| value.as_str()?.parse().ok())
+}
+
+fn unix_nanos(value: &Value) -> Option<DateTime<Utc>> {
+    let nanos = int64(value).filter(|n| *n > 0)?;
+    Some(Utc.timestamp_nanos(nanos))
+}
+
+fn any_value(value: &Value) -> Option<AttributeValue> {
+    if let Some(s) = value.get("stringValue").and_then(Value::as_str) {
+        return Some(AttributeValue::String(s.to_string()));
+    }
+    if let Some(b) = value.get("boolValue").and_then(Value::as_bool) {
+        return Some(AttributeValue::Bool(b));
+    }
+    if let Some(i) = value.get("intValue").and_then(int64) {
+        return Some(AttributeValue::Int(i));
+    }
+    if let Some(f) = value.get("doubleValue").and_then(Value::as_f64) {
+        return Some(AttributeValue::Float(f));
+    }
+    if let Some(values) = value.get("arrayValue").and_then(|a| a.get("values")).and_then(Value::as_array) {
+        return Some(AttributeValue::Array(values.iter().filter_map(any_value).collect()));
+    }
+    // Nested maps and bytes have no attribute equivalent and are kept as their JSON text
+    value
+        .get("kvlistValue")
+        .or_else(|| value.get("bytesValue"))
+        .map(|v| AttributeValue::String(v.to_string()))
+}
+
+fn attributes(value: &Value) -> HashMap<String, AttributeValue> {
+    value
+        .as_array()
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(|kv| Some((kv.get("key")?.as_str()?.to_string(), any_value(kv.get("value")?)?)))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn body_text(value: &Value) -> String {
+    match any_value(value) {
+        Some(AttributeValue::String(s)) => s,
+        Some(other) => super::attribute_string(&other),
+        None => String::new(),
+    }
+}
+
+fn log_record(value: &Value, resource: &HashMap<String, AttributeValue>) -> ObservabilityResult<LogRecord> {
+    let observed = unix_nanos(&value["observedTimeUnixNano"]);
+    // The spec falls back to the observed time when the event time is unknown
+    let timestamp = unix_nanos(&value["timeUnixNano"])
+        .or(observed)
+        .ok_or_else(|| invalid("log record has no timestamp"))?;
+    let severity = value["severityNumber"]
+        .as_i64()
+        .and_then(|n| LogSeverity::from_number(n as i32))
+        .or_else(|| value["severityText"].as_str().and_then(LogSeverity::from_text))
+        .unwrap_or(LogSeverity::Info);
+    let id = |key: &str| value[key].as_str().filter(|id| !id.is_empty()).map(str::to_string);
+    LogRecord {
+        timestamp,
+        observed_timestamp: observed,
+        severity,
+        body: body_text(&value["body"]),
+        attributes: attributes(&value["attributes"]),
+        resource: resource.clone(),
+        trace_id: id("traceId"),
+        span_id: id("spanId"),
+    }
+    .normalize()
+}
+
+// Decodes an OTLP/HTTP JSON ExportLogsServiceRequest
+pub fn decode_export_request(payload: &Value) -> ObservabilityResult<Vec<LogRecord>> {
+    let resource_logs = payload["resourceLogs"]
+        .as_array()
+        .ok_or_else(|| invalid("missing resourceLogs"))?;
+    let mut records = Vec::new();
+    for resource_log in resource_logs {
+        let resource = attributes(&resource_log["resource"]["attributes"]);
+        for scope_log in resource_log["scopeLogs"].as_array().into_iter().flatten() {
+            for record in scope_log["logRecords"].as_array().into_iter().flatten() {
+                records.push(log_record(record, &resource)?);
+            }
+        }
+    }
+    Ok(records)
+}
+
+#[derive(Debug)]
+struct OtlpError(StatusCode, String);
+
+impl IntoResponse for OtlpError {
+    fn into_response(self) -> Response {
+        (self.0, Json(json!({ "code": self.0.as_u16(), "message": self.1 }))).into_response()
+    }
+}
+
+impl From<ObservabilityError> for OtlpError {
+    fn from(error: ObservabilityError) -> Self {
+        match error {
+            ObservabilityError::Validation(msg) => OtlpError(StatusCode::BAD_REQUEST, msg),
+            ObservabilityError::Throttled(msg) => OtlpError(StatusCode::TOO_MANY_REQUESTS, msg),
+            ObservabilityError::Unavailable(msg) | ObservabilityError::Storage(msg) => {
+                OtlpError(StatusCode::SERVICE_UNAVAILABLE, msg)
+            }
+            other => OtlpError(StatusCode::INTERNAL_SERVER_ERROR, other.to_string()),
+        }
+    }
+}
+
+async fn export_logs(
+    State(manager): State<Arc<dyn LogsManager>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<Value>, OtlpError> {
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    if !content_type.starts_with("application/json") {
+        return Err(OtlpError(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "Only the OTLP/HTTP JSON encoding is supported".to_string(),
+        ));
+    }
+    let payload: Value = serde_json::from_slice(&body).map_err(|e| OtlpError(StatusCode::BAD_REQUEST, e.to_string()))?;
+    let records = decode_export_request(&payload)?;
+    let count = manager.ingest(records).await?;
+    debug!("Ingested {} OTLP log records", count);
+    Ok(Json(json!({ "partialSuccess": {} })))
+}
+
+// Serves POST /v1/logs so services can export logs with their OTLP/HTTP exporters
+pub fn otlp_logs_router(manager: Arc<dyn LogsManager>) -> Router {
+    Router::new().route(OTLP_LOGS_PATH, post(export_logs)).with_state(manager)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logs::{InMemoryLogsManager, LogQuery};
+
+    #[tokio::test]
+    async fn test_otlp_json_ingest() {
+        let payload = json!({
+            "resourceLogs": [{
+                "resource": {"attributes": [{"key": "service.name", "value": {"stringValue": "checkout"}}]},
+                "scopeLogs": [{
+                    "scope": {"name": "checkout.logger"},
+                    "logRecords": [{
+                        "timeUnixNano": "1709294400000000000",
+                        "severityNumber": 17,
+                        "severityText": "ERROR",
+                        "body": {"stringValue": "payment declined"},
+                        "attributes": [
+                            {"key": "retry", "value": {"intValue": "3"}},
+                            {"key": "card.present", "value": {"boolValue": false}}
+                        ],
+                        "traceId": "5B8EFFF798038103D269B633813FC60C",
+                        "spanId": "EEE19B7EC3C1B174"
+                    }, {
+                        "observedTimeUnixNano": "1709294401000000000",
+                        "severityText": "warning",
+                        "body": {"stringValue": "slow upstream"},
+                        "traceId": "",
+                        "spanId": ""
+                    }]
+                }]
+            }]
+        });
+
+        let records = decode_export_request(&payload).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].severity, LogSeverity::Error);
+        assert_eq!(records[0].trace_id.as_deref(), Some("5b8efff798038103d269b633813fc60c"));
+        assert!(matches!(records[0].attributes["retry"], AttributeValue::Int(3)));
+        assert!(matches!(&records[0].resource["service.name"], AttributeValue::String(s) if s == "checkout"));
+        assert_eq!(records[1].severity, LogSeverity::Warn);
+        assert_eq!(records[1].timestamp, records[1].observed_timestamp.unwrap());
+        assert!(records[1].trace_id.is_none());
+
+        let manager: Arc<dyn LogsManager> = Arc::new(InMemoryLogsManager::new());
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_TYPE, "application/json".parse().unwrap());
+        let body = Bytes::from(payload.to_string());
+        let accepted = export_logs(State(manager.clone()), headers.clone(), body).await.unwrap();
+        assert_eq!(accepted.0, json!({ "partialSuccess": {} }));
+        let stored = manager
+            .query(LogQuery {
+                attributes: HashMap::from([("card.present".to_string(), "false".to_string())]),
+                ..LogQuery::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(stored.records.len(), 1);
+
+        let rejected = export_logs(State(manager.clone()), headers, Bytes::from(r#"{"resourceLogs": 1}"#)).await;
+        assert!(matches!(rejected, Err(OtlpError(StatusCode::BAD_REQUEST, _))));
+        let protobuf = export_logs(State(manager), HeaderMap::new(), Bytes::new()).await;
+        assert!(matches!(protobuf, Err(OtlpError(StatusCode::UNSUPPORTED_MEDIA_TYPE, _))));
+    }
+}
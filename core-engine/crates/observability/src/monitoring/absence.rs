@@ -0,0 +1,355 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use tracing::warn;
+
+use super::{AlertCondition, AlertEvent, AlertRule, AlertState, MetricDataPoint, MetricsManager};
+use crate::error::{ObservabilityError, ObservabilityResult};
+
+#[derive(Debug, Clone)]
+pub struct AbsenceConfig {
+    // Until a rule has seen one point it stays quiet, so a rule created ahead of the job it
+    // watches doesn't page. With this off, silence since the first evaluation counts as absence.
+    pub require_first_point: bool,
+    // Distinct dimension combinations tracked per rule
+    pub max_series_per_rule: usize,
+}
+
+impl Default for AbsenceConfig {
+    fn default() -> Self {
+        Self { require_first_point: true, max_series_per_rule: 1000 }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeriesAbsence {
+    pub dimensions: HashMap<String, String>,
+    // None only for the placeholder series of a rule that has never seen data
+    pub last_seen: Option<DateTime<Utc>>,
+    pub overdue_seconds: i64,
+    pub firing: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AbsenceEvaluation {
+    pub rule_id: String,
+    pub evaluated_at: DateTime<Utc>,
+    pub series: Vec<SeriesAbsence>,
+    // Firing and resolved events raised by this evaluation, one per series that changed
+    pub events: Vec<AlertEvent>,
+}
+
+struct SeriesState {
+    dimensions: HashMap<String, String>,
+    last_seen: Option<DateTime<Utc>>,
+    firing_since: Option<DateTime<Utc>>,
+}
+
+struct RuleState {
+    tracked_since: DateTime<Utc>,
+    series: BTreeMap<String, SeriesState>,
+}
+
+// Placeholder key for "nothing has arrived yet", used when `require_first_point` is off
+const NEVER_SEEN: &str = "";
+
+fn series_key(dimensions: &HashMap<String, String>) -> String {
+    let sorted: BTreeMap<_, _> = dimensions.iter().collect();
+    let parts: Vec<String> = sorted.into_iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+    format!("{{{}}}", parts.join(","))
+}
+
+// Tracks when each series matching a rule's query last reported and fires per series once
+// it has been silent for longer than the expected interval plus grace
+pub struct AbsenceDetector {
+    config: AbsenceConfig,
+    rules: RwLock<HashMap<String, RuleState>>,
+}
+
+impl Default for AbsenceDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AbsenceDetector {
+    pub fn new() -> Self {
+        Self::with_config(AbsenceConfig::default())
+    }
+
+    pub fn with_config(config: AbsenceConfig) -> Self {
+        Self { config, rules: RwLock::new(HashMap::new()) }
+    }
+
+    // Only the recent window is queried; older last-seen times are remembered between evaluations
+    pub async fn evaluate_rule(
+        &self,
+        metrics: &dyn MetricsManager,
+        rule: &AlertRule,
+        now: DateTime<Utc>,
+    ) -> ObservabilityResult<AbsenceEvaluation> {
+        let (interval, grace) = absence_window(rule)?;
+        let mut query = rule.query.clone();
+        query.start_time = now - chrono::Duration::seconds(interval + grace + i64::from(rule.evaluation_interval.max(0)));
+        query.end_time = now;
+        let points = metrics.get_metric_data(query).await?;
+        self.evaluate(rule, &points, now).await
+    }
+
+    // `points` are whatever the rule's query returned since the last evaluation; points after
+    // `now` are ignored
+    pub async fn evaluate(
+        &self,
+        rule: &AlertRule,
+        points: &[MetricDataPoint],
+        now: DateTime<Utc>,
+    ) -> ObservabilityResult<AbsenceEvaluation> {
+        let (interval, grace) = absence_window(rule)?;
+        let wanted = rule.query.dimensions.clone().unwrap_or_default();
+
+        let mut latest: BTreeMap<String, (&HashMap<String, String>, DateTime<Utc>)> = BTreeMap::new();
+        for point in points {
+            let matches = point.name == rule.query.metric_name
+                && point.namespace == rule.query.namespace
+                && wanted.iter().all(|(k, v)| point.dimensions.get(k) == Some(v));
+            if !matches || point.timestamp > now {
+                continue;
+            }
+            let entry = latest.entry(series_key(&point.dimensions)).or_insert((&point.dimensions, point.timestamp));
+            entry.1 = entry.1.max(point.timestamp);
+        }
+
+        let mut rules = self.rules.write().await;
+        let state = rules.entry(rule.id.clone()).or_insert_with(|| RuleState { tracked_since: now, series: BTreeMap::new() });
+
+        let new_series = latest.keys().filter(|key| !state.series.contains_key(*key)).count();
+        let tracked = state.series.keys().filter(|key| key.as_str() != NEVER_SEEN).count();
+        if tracked + new_series > self.config.max_series_per_rule {
+            warn!("Absence rule {} matched {} series", rule.id, tracked + new_series);
+            return Err(ObservabilityError::Validation(format!(
+                "Absence rule {} matches more than {} dimension combinations; narrow its query dimensions",
+                rule.id, self.config.max_series_per_rule
+            )));
+        }
+
+        let mut events = Vec::new();
+        if !latest.is_empty() {
+            if let Some(placeholder) = state.series.remove(NEVER_SEEN) {
+                if placeholder.firing_since.is_some() {
+                    events.push(alert_event(rule, AlertState::Resolved, &placeholder.dimensions, None, 0, now));
+                }
+            }
+        } else if state.series.is_empty() && !self.config.require_first_point {
+            state
+                .series
+                .insert(NEVER_SEEN.to_string(), SeriesState { dimensions: wanted, last_seen: None, firing_since: None });
+        }
+        for (key, (dimensions, seen)) in latest {
+            let series = state
+                .series
+                .entry(key)
+                .or_insert_with(|| SeriesState { dimensions: dimensions.clone(), last_seen: None, firing_since: None });
+            series.last_seen = Some(series.last_seen.map_or(seen, |last| last.max(seen)));
+        }
+
+        let tracked_since = state.tracked_since;
+        let mut report = Vec::with_capacity(state.series.len());
+        for series in state.series.values_mut() {
+            let reference = series.last_seen.unwrap_or(tracked_since);
+            let overdue = (now - reference).num_seconds() - interval - grace;
+            if overdue > 0 {
+                if series.firing_since.is_none() {
+                    series.firing_since = Some(now);
+                    events.push(alert_event(rule, AlertState::Firing, &series.dimensions, series.last_seen, overdue, now));
+                }
+            } else if series.firing_since.take().is_some() {
+                events.push(alert_event(rule, AlertState::Resolved, &series.dimensions, series.last_seen, 0, now));
+            }
+            report.push(SeriesAbsence {
+                dimensions: series.dimensions.clone(),
+                last_seen: series.last_seen,
+                overdue_seconds: overdue.max(0),
+                firing: series.firing_since.is_some(),
+            });
+        }
+
+        Ok(AbsenceEvaluation { rule_id: rule.id.clone(), evaluated_at: now, series: report, events })
+    }
+
+    // Drops tracked series for a deleted or edited rule
+    pub async fn forget_rule(&self, rule_id: &str) {
+        self.rules.write().await.remove(rule_id);
+    }
+}
+
+fn absence_window(rule: &AlertRule) -> ObservabilityResult<(i64, i64)> {
+    let AlertCondition::Absence { expected_interval_seconds, grace_seconds } = &rule.condition else {
+        return Err(ObservabilityError::Validation(format!("Alert rule {} is not an absence rule", rule.id)));
+    };
+    if *expected_interval_seconds <= 0 || *grace_seconds < 0 {
+        return Err(ObservabilityError::Validation(format!(
+            "Absence rule {} needs a positive interval and a non-negative grace period",
+            rule.id
+        )));
+    }
+    Ok((i64::from(*expected_interval_seconds), i64::from(*grace_seconds)))
+}
+
+fn alert_event(
+    rule: &AlertRule,
+    state: AlertState,
+    dimensions: &HashMap<String, String>,
+    last_seen: Option<DateTime<Utc>>,
+    overdue_seconds: i64,
+    now: DateTime<Utc>,
+) -> AlertEvent {
+    let resolved = matches!(state, AlertState::Resolved);
+    let series = series_key(dimensions);
+    let message = match (resolved, last_seen) {
+        (true, _) => format!("{} {} is reporting again", rule.name, series),
+        (false, Some(last_seen)) => format!("{} {} has not reported since {}", rule.name, series, last_seen.to_rfc3339()),
+        (false, None) => format!("{} {} has never reported", rule.name, series),
+    };
+    let mut metadata = dimensions.clone();
+    metadata.insert("series".to_string(), series.clone());
+    metadata.insert("overdue_seconds".to_string(), overdue_seconds.to_string());
+    if let Some(last_seen) = last_seen {
+        metadata.insert("last_seen".to_string(), last_seen.to_rfc3339());
+    }
+    AlertEvent {
+        id: format!("{}-{}-{}", rule.id, series, now.timestamp()),
+        rule_id: rule.id.clone(),
+        severity: rule.severity.clone(),
+        state,
+        message,
+        value: overdue_seconds as f64,
+        timestamp: now,
+        resolved_at: resolved.then_some(now),
+        metadata,
+        actions: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{AggregationType, AlertActions, AlertSeverity, MetricQuery, MetricValue};
+    use chrono::{Duration, TimeZone};
+
+    fn rule() -> AlertRule {
+        AlertRule {
+            id: "nightly-backup".to_string(),
+            name: "Nightly backup heartbeat".to_string(),
+            description: String::new(),
+            severity: AlertSeverity::Error,
+            query: MetricQuery {
+                metric_name: "backup_completed".to_string(),
+                namespace: "Sirsi/Jobs".to_string(),
+                dimensions: Some(HashMap::from([("env".to_string(), "prod".to_string())])),
+                aggregation: AggregationType::Count,
+                period: 60,
+                start_time: Utc.timestamp_opt(0, 0).unwrap(),
+                end_time: Utc.timestamp_opt(0, 0).unwrap(),
+                include_exemplars: false,
+            },
+            condition: AlertCondition::Absence { expected_interval_seconds: 3600, grace_seconds: 300 },
+            notification_channels: Vec::new(),
+            evaluation_interval: 60,
+            enabled: true,
+            alert_actions: AlertActions::default(),
+        }
+    }
+
+    fn heartbeat(database: &str, at: DateTime<Utc>) -> MetricDataPoint {
+        MetricDataPoint {
+            name: "backup_completed".to_string(),
+            namespace: "Sirsi/Jobs".to_string(),
+            dimensions: HashMap::from([
+                ("env".to_string(), "prod".to_string()),
+                ("database".to_string(), database.to_string()),
+            ]),
+            timestamp: at,
+            value: MetricValue::Single(1.0),
+            exemplars: Vec::new(),
+        }
+    }
+
+    fn states(evaluation: &AbsenceEvaluation) -> Vec<(String, bool)> {
+        evaluation
+            .events
+            .iter()
+            .map(|event| (event.metadata["database"].clone(), matches!(event.state, AlertState::Firing)))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_fires_after_interval_plus_grace_and_resolves_on_resume() {
+        let t0 = Utc.with_ymd_and_hms(2024, 5, 1, 0, 0, 0).unwrap();
+        let detector = AbsenceDetector::new();
+        let rule = rule();
+
+        let evaluation = detector
+            .evaluate(&rule, &[heartbeat("orders", t0), heartbeat("users", t0)], t0 + Duration::minutes(1))
+            .await
+            .unwrap();
+        assert_eq!(evaluation.series.len(), 2);
+        assert!(evaluation.events.is_empty());
+
+        // "users" keeps reporting; "orders" goes quiet after t0
+        let users = [heartbeat("users", t0 + Duration::hours(1))];
+        let evaluation = detector.evaluate(&rule, &users, t0 + Duration::minutes(65)).await.unwrap();
+        assert!(evaluation.events.is_empty(), "exactly interval + grace is not yet overdue");
+
+        let evaluation = detector.evaluate(&rule, &[], t0 + Duration::minutes(66)).await.unwrap();
+        assert_eq!(states(&evaluation), vec![("orders".to_string(), true)]);
+        assert_eq!(evaluation.events[0].metadata["overdue_seconds"], "60");
+        assert!(evaluation.series.iter().any(|s| s.firing && s.overdue_seconds == 60));
+
+        // Still silent: no duplicate page
+        let evaluation = detector.evaluate(&rule, &[], t0 + Duration::minutes(90)).await.unwrap();
+        assert!(evaluation.events.is_empty());
+
+        let resumed = [heartbeat("orders", t0 + Duration::minutes(95))];
+        let evaluation = detector.evaluate(&rule, &resumed, t0 + Duration::minutes(96)).await.unwrap();
+        assert_eq!(states(&evaluation), vec![("orders".to_string(), false)]);
+        assert!(evaluation.series.iter().all(|s| !s.firing));
+
+        // Points outside the rule's dimension filter never create series
+        let mut staging = heartbeat("orders", t0 + Duration::minutes(97));
+        staging.dimensions.insert("env".to_string(), "staging".to_string());
+        let evaluation = detector.evaluate(&rule, &[staging], t0 + Duration::minutes(98)).await.unwrap();
+        assert_eq!(evaluation.series.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_never_seen_suppression_and_cardinality_limit() {
+        let t0 = Utc.with_ymd_and_hms(2024, 5, 1, 0, 0, 0).unwrap();
+        let rule = rule();
+
+        let quiet = AbsenceDetector::new();
+        for hours in [0, 2, 24] {
+            let evaluation = quiet.evaluate(&rule, &[], t0 + Duration::hours(hours)).await.unwrap();
+            assert!(evaluation.events.is_empty() && evaluation.series.is_empty());
+        }
+
+        // Without suppression, silence since the first evaluation pages, and the first point resolves it
+        let eager = AbsenceDetector::with_config(AbsenceConfig { require_first_point: false, ..AbsenceConfig::default() });
+        assert!(eager.evaluate(&rule, &[], t0).await.unwrap().events.is_empty());
+        let evaluation = eager.evaluate(&rule, &[], t0 + Duration::hours(2)).await.unwrap();
+        assert_eq!(evaluation.events.len(), 1);
+        assert!(evaluation.events[0].message.contains("never reported"));
+        let first = [heartbeat("orders", t0 + Duration::hours(3))];
+        let evaluation = eager.evaluate(&rule, &first, t0 + Duration::hours(3)).await.unwrap();
+        assert_eq!(evaluation.events.len(), 1);
+        assert!(matches!(evaluation.events[0].state, AlertState::Resolved));
+        assert_eq!(evaluation.series.len(), 1);
+
+        let bounded = AbsenceDetector::with_config(AbsenceConfig { max_series_per_rule: 2, ..AbsenceConfig::default() });
+        let three: Vec<_> = ["a", "b", "c"].iter().map(|db| heartbeat(db, t0)).collect();
+        let err = bounded.evaluate(&rule, &three, t0).await.unwrap_err();
+        assert!(matches!(err, ObservabilityError::Validation(_)));
+        assert!(bounded.evaluate(&rule, &three[..2], t0).await.is_ok());
+    }
+}
@@ -0,0 +1,457 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+use super::{AlertCondition, AlertEvent, AlertRule, AlertState, DeviationType, MetricDataPoint, MetricValue, MetricsManager};
+use crate::error::{ObservabilityError, ObservabilityResult};
+
+const HOURS_PER_WEEK: usize = 168;
+
+#[derive(Debug, Clone)]
+pub struct AnomalyConfig {
+    // History used to learn baselines, ending at the evaluation time
+    pub training_window: Duration,
+    // A seasonal slot needs this many samples (roughly weeks of history) before it's trusted
+    pub min_samples_per_slot: usize,
+    // Fewer training samples than this and no baseline is computed at all
+    pub min_training_samples: usize,
+    // MAD floor as a fraction of the slot median, so a perfectly flat slot doesn't turn
+    // noise into an unbounded deviation
+    pub mad_floor_ratio: f64,
+    pub profile_refresh: Duration,
+}
+
+impl Default for AnomalyConfig {
+    fn default() -> Self {
+        Self {
+            training_window: Duration::from_secs(28 * 24 * 3600),
+            min_samples_per_slot: 3,
+            min_training_samples: 10,
+            mad_floor_ratio: 0.05,
+            profile_refresh: Duration::from_secs(3600),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BaselineKind {
+    Seasonal,
+    StandardDeviation,
+    PercentageChange,
+    // Not enough history for any baseline; never anomalous
+    Insufficient,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnomalyEvaluation {
+    pub rule_id: String,
+    pub evaluated_at: DateTime<Utc>,
+    pub value: f64,
+    pub expected: f64,
+    // In the baseline's unit: MADs, standard deviations or percent
+    pub deviation: f64,
+    pub baseline: BaselineKind,
+    // A seasonal rule was evaluated against the stddev fallback
+    pub cold_start: bool,
+    pub anomalous: bool,
+    pub firing: bool,
+    // Set on the evaluation that starts or resolves the alert
+    pub event: Option<AlertEvent>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct SlotBaseline {
+    median: f64,
+    mad: f64,
+    samples: usize,
+}
+
+#[derive(Debug, Clone)]
+struct SeasonalProfile {
+    built_at: DateTime<Utc>,
+    slots: Vec<Option<SlotBaseline>>,
+}
+
+#[derive(Default)]
+struct SeriesState {
+    profile: Option<SeasonalProfile>,
+    deviating_since: Option<DateTime<Utc>>,
+    firing_since: Option<DateTime<Utc>>,
+    cold_start: bool,
+}
+
+fn hour_of_week(at: DateTime<Utc>) -> usize {
+    at.weekday().num_days_from_monday() as usize * 24 + at.hour() as usize
+}
+
+fn point_value(value: &MetricValue) -> f64 {
+    match value {
+        MetricValue::Single(v) => *v,
+        MetricValue::Multiple(values) if values.is_empty() => 0.0,
+        MetricValue::Multiple(values) => values.iter().sum::<f64>() / values.len() as f64,
+        MetricValue::Distribution { count: 0, .. } => 0.0,
+        MetricValue::Distribution { sum, count, .. } => sum / *count as f64,
+    }
+}
+
+fn median(values: &mut [f64]) -> f64 {
+    values.sort_by(|a, b| a.total_cmp(b));
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    }
+}
+
+impl SeasonalProfile {
+    fn learn(training: &[(DateTime<Utc>, f64)], built_at: DateTime<Utc>) -> Self {
+        let mut by_slot: Vec<Vec<f64>> = vec![Vec::new(); HOURS_PER_WEEK];
+        for (at, value) in training {
+            by_slot[hour_of_week(*at)].push(*value);
+        }
+        let slots = by_slot
+            .into_iter()
+            .map(|mut values| {
+                if values.is_empty() {
+                    return None;
+                }
+                let median = median(&mut values);
+                let mut deviations: Vec<f64> = values.iter().map(|v| (v - median).abs()).collect();
+                Some(SlotBaseline { median, mad: self::median(&mut deviations), samples: values.len() })
+            })
+            .collect();
+        Self { built_at, slots }
+    }
+}
+
+fn mean_stddev(values: &[f64]) -> (f64, f64) {
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64;
+    (mean, variance.sqrt())
+}
+
+fn scaled_deviation(value: f64, expected: f64, spread: f64) -> f64 {
+    let diff = (value - expected).abs();
+    if spread > 0.0 {
+        diff / spread
+    } else if diff > 0.0 {
+        f64::INFINITY
+    } else {
+        0.0
+    }
+}
+
+pub struct AnomalyDetector {
+    config: AnomalyConfig,
+    series: RwLock<HashMap<String, SeriesState>>,
+}
+
+impl Default for AnomalyDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AnomalyDetector {
+    pub fn new() -> Self {
+        Self::with_config(AnomalyConfig::default())
+    }
+
+    pub fn with_config(config: AnomalyConfig) -> Self {
+        Self { config, series: RwLock::new(HashMap::new()) }
+    }
+
+    // Fetches the training window for the rule's query and evaluates the latest point
+    pub async fn evaluate_rule(
+        &self,
+        metrics: &dyn MetricsManager,
+        rule: &AlertRule,
+        now: DateTime<Utc>,
+    ) -> ObservabilityResult<AnomalyEvaluation> {
+        let mut query = rule.query.clone();
+        query.start_time = now - chrono::Duration::from_std(self.config.training_window).unwrap_or(chrono::Duration::MAX);
+        query.end_time = now;
+        let points = metrics.get_metric_data(query).await?;
+        self.evaluate(rule, &points, now).await
+    }
+
+    // `points` holds the rule's series up to `now`; the newest point is the one judged
+    // and everything before it inside the training window is history
+    pub async fn evaluate(
+        &self,
+        rule: &AlertRule,
+        points: &[MetricDataPoint],
+        now: DateTime<Utc>,
+    ) -> ObservabilityResult<AnomalyEvaluation> {
+        let AlertCondition::Anomaly { deviation_type, sensitivity, duration_seconds } = &rule.condition else {
+            return Err(ObservabilityError::Validation(format!("Alert rule {} is not an anomaly rule", rule.id)));
+        };
+        let training_start =
+            now - chrono::Duration::from_std(self.config.training_window).unwrap_or(chrono::Duration::MAX);
+        let mut series: Vec<(DateTime<Utc>, f64)> = points
+            .iter()
+            .filter(|p| p.timestamp > training_start && p.timestamp <= now)
+            .map(|p| (p.timestamp, point_value(&p.value)))
+            .collect();
+        series.sort_by_key(|(at, _)| *at);
+        let Some((current_at, value)) = series.pop() else {
+            return Err(ObservabilityError::NotFound(format!("No data for anomaly rule {}", rule.id)));
+        };
+
+        let mut states = self.series.write().await;
+        let state = states.entry(rule.id.clone()).or_default();
+        let (baseline, expected, deviation) = self.baseline(rule, state, deviation_type, &series, current_at, value, now);
+
+        let anomalous = baseline != BaselineKind::Insufficient && deviation > *sensitivity;
+        let mut event = None;
+        if anomalous {
+            let since = *state.deviating_since.get_or_insert(now);
+            if state.firing_since.is_none() && (now - since).num_seconds() >= i64::from(*duration_seconds) {
+                state.firing_since = Some(now);
+                event = Some(alert_event(rule, AlertState::Firing, value, expected, deviation, baseline, now));
+            }
+        } else {
+            state.deviating_since = None;
+            if state.firing_since.take().is_some() {
+                event = Some(alert_event(rule, AlertState::Resolved, value, expected, deviation, baseline, now));
+            }
+        }
+
+        Ok(AnomalyEvaluation {
+            rule_id: rule.id.clone(),
+            evaluated_at: now,
+            value,
+            expected,
+            deviation,
+            baseline,
+            cold_start: state.cold_start,
+            anomalous,
+            firing: state.firing_since.is_some(),
+            event,
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn baseline(
+        &self,
+        rule: &AlertRule,
+        state: &mut SeriesState,
+        deviation_type: &DeviationType,
+        training: &[(DateTime<Utc>, f64)],
+        current_at: DateTime<Utc>,
+        value: f64,
+        now: DateTime<Utc>,
+    ) -> (BaselineKind, f64, f64) {
+        if training.len() < self.config.min_training_samples {
+            return (BaselineKind::Insufficient, value, 0.0);
+        }
+        let values: Vec<f64> = training.iter().map(|(_, v)| *v).collect();
+        let (mean, stddev) = mean_stddev(&values);
+        match deviation_type {
+            DeviationType::StandardDeviation => (BaselineKind::StandardDeviation, mean, scaled_deviation(value, mean, stddev)),
+            DeviationType::PercentageChange => {
+                (BaselineKind::PercentageChange, mean, scaled_deviation(value, mean, mean.abs() / 100.0))
+            }
+            DeviationType::Seasonal => {
+                let refresh = chrono::Duration::from_std(self.config.profile_refresh).unwrap_or(chrono::Duration::MAX);
+                let stale = state
+                    .profile
+                    .as_ref()
+                    .is_none_or(|p| now < p.built_at || now - p.built_at >= refresh);
+                if stale {
+                    state.profile = Some(SeasonalProfile::learn(training, now));
+                }
+                let slot = state
+                    .profile
+                    .as_ref()
+                    .and_then(|p| p.slots[hour_of_week(current_at)])
+                    .filter(|slot| slot.samples >= self.config.min_samples_per_slot);
+                match slot {
+                    Some(slot) => {
+                        if std::mem::take(&mut state.cold_start) {
+                            info!("Anomaly rule {} has enough history for its seasonal baseline", rule.id);
+                        }
+                        let mad = slot.mad.max(slot.median.abs() * self.config.mad_floor_ratio);
+                        (BaselineKind::Seasonal, slot.median, scaled_deviation(value, slot.median, mad))
+                    }
+                    None => {
+                        if !state.cold_start {
+                            warn!(
+                                "Anomaly rule {} lacks {} samples for hour-of-week {}; falling back to stddev baseline",
+                                rule.id,
+                                self.config.min_samples_per_slot,
+                                hour_of_week(current_at)
+                            );
+                            state.cold_start = true;
+                        }
+                        (BaselineKind::StandardDeviation, mean, scaled_deviation(value, mean, stddev))
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn alert_event(
+    rule: &AlertRule,
+    state: AlertState,
+    value: f64,
+    expected: f64,
+    deviation: f64,
+    baseline: BaselineKind,
+    now: DateTime<Utc>,
+) -> AlertEvent {
+    let resolved = matches!(state, AlertState::Resolved);
+    let message = if resolved {
+        format!("{} is back within its expected range", rule.name)
+    } else {
+        format!("{} is {:.1} from its expected value of {:.2}", rule.name, deviation, expected)
+    };
+    AlertEvent {
+        id: format!("{}-{}", rule.id, now.timestamp()),
+        rule_id: rule.id.clone(),
+        severity: rule.severity.clone(),
+        state,
+        message,
+        value,
+        timestamp: now,
+        resolved_at: resolved.then_some(now),
+        metadata: HashMap::from([
+            ("baseline".to_string(), format!("{:?}", baseline)),
+            ("expected".to_string(), expected.to_string()),
+            ("deviation".to_string(), deviation.to_string()),
+        ]),
+        actions: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{AggregationType, AlertActions, AlertSeverity, MetricQuery};
+    use chrono::TimeZone;
+
+    fn rule(deviation_type: DeviationType, sensitivity: f64) -> AlertRule {
+        AlertRule {
+            id: "checkout-rps".to_string(),
+            name: "Checkout request rate".to_string(),
+            description: String::new(),
+            severity: AlertSeverity::Warning,
+            query: MetricQuery {
+                metric_name: "requests".to_string(),
+                namespace: "Sirsi/Checkout".to_string(),
+                dimensions: None,
+                aggregation: AggregationType::Sum,
+                period: 3600,
+                start_time: Utc.timestamp_opt(0, 0).unwrap(),
+                end_time: Utc.timestamp_opt(0, 0).unwrap(),
+                include_exemplars: false,
+            },
+            condition: AlertCondition::Anomaly { deviation_type, sensitivity, duration_seconds: 3600 },
+            notification_channels: Vec::new(),
+            evaluation_interval: 3600,
+            enabled: true,
+            alert_actions: AlertActions::default(),
+        }
+    }
+
+    // Hourly samples starting on a Monday: a 12:00-16:00 weekday peak five times the
+    // overnight level, quieter weekends and a little deterministic noise
+    fn weekly_series(weeks: usize) -> Vec<MetricDataPoint> {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        (0..weeks * HOURS_PER_WEEK)
+            .map(|i| {
+                let at = start + chrono::Duration::hours(i as i64);
+                let peak = match at.hour() {
+                    12..=15 => 4.0,
+                    10 | 11 | 16 | 17 => 2.0,
+                    _ => 0.0,
+                };
+                let weekend = if at.weekday().num_days_from_monday() >= 5 { 0.3 } else { 1.0 };
+                let noise = ((i * 37 + i / HOURS_PER_WEEK * 3) % 7) as f64 - 3.0;
+                MetricDataPoint {
+                    name: "requests".to_string(),
+                    namespace: "Sirsi/Checkout".to_string(),
+                    dimensions: HashMap::new(),
+                    timestamp: at,
+                    value: MetricValue::Single(100.0 * (1.0 + peak) * weekend + noise),
+                    exemplars: Vec::new(),
+                }
+            })
+            .collect()
+    }
+
+    async fn run_week(detector: &AnomalyDetector, rule: &AlertRule, series: &[MetricDataPoint]) -> Vec<AnomalyEvaluation> {
+        let mut evaluations = Vec::new();
+        for end in series.len() - HOURS_PER_WEEK..series.len() {
+            let now = series[end].timestamp;
+            evaluations.push(detector.evaluate(rule, &series[..=end], now).await.unwrap());
+        }
+        evaluations
+    }
+
+    #[tokio::test]
+    async fn test_seasonal_baseline_fires_once_on_spike() {
+        let mut series = weekly_series(5);
+        // Wednesday 03:00-05:00 of the last week, overnight when the normal level is ~100
+        let spike = 4 * HOURS_PER_WEEK + 2 * 24 + 3;
+        for point in &mut series[spike..spike + 3] {
+            point.value = MetricValue::Single(point_value(&point.value) + 300.0);
+        }
+
+        let seasonal = rule(DeviationType::Seasonal, 8.0);
+        let evaluations = run_week(&AnomalyDetector::new(), &seasonal, &series).await;
+        assert!(evaluations.iter().all(|e| e.baseline == BaselineKind::Seasonal && !e.cold_start));
+        let fired: Vec<_> = evaluations
+            .iter()
+            .filter_map(|e| e.event.as_ref())
+            .filter(|event| matches!(event.state, AlertState::Firing))
+            .collect();
+        assert_eq!(fired.len(), 1);
+        // The spike has to persist for an hour before it pages
+        assert_eq!(fired[0].timestamp, series[spike + 1].timestamp);
+        let anomalous: Vec<_> = evaluations.iter().filter(|e| e.anomalous).map(|e| e.evaluated_at).collect();
+        assert_eq!(anomalous, series[spike..spike + 3].iter().map(|p| p.timestamp).collect::<Vec<_>>());
+        assert!(evaluations.iter().any(|e| e.event.as_ref().is_some_and(|ev| matches!(ev.state, AlertState::Resolved))));
+
+        // A flat stddev baseline on the same data pages on every weekday peak
+        let flat = rule(DeviationType::StandardDeviation, 2.0);
+        let evaluations = run_week(&AnomalyDetector::new(), &flat, &series).await;
+        let fired = evaluations
+            .iter()
+            .filter(|e| e.event.as_ref().is_some_and(|ev| matches!(ev.state, AlertState::Firing)))
+            .count();
+        assert!(fired >= 5);
+    }
+
+    #[tokio::test]
+    async fn test_cold_start_falls_back_to_stddev() {
+        let series = weekly_series(2);
+        let detector = AnomalyDetector::new();
+        let seasonal = rule(DeviationType::Seasonal, 8.0);
+
+        let now = series.last().unwrap().timestamp;
+        let evaluation = detector.evaluate(&seasonal, &series, now).await.unwrap();
+        assert_eq!(evaluation.baseline, BaselineKind::StandardDeviation);
+        assert!(evaluation.cold_start);
+
+        let evaluation = detector.evaluate(&seasonal, &series[..5], series[4].timestamp).await.unwrap();
+        assert_eq!(evaluation.baseline, BaselineKind::Insufficient);
+        assert!(!evaluation.anomalous);
+
+        let threshold = AlertRule {
+            condition: AlertCondition::Threshold {
+                operator: super::super::ComparisonOperator::GreaterThan,
+                threshold: 1.0,
+                duration_seconds: 0,
+            },
+            ..seasonal
+        };
+        assert!(detector.evaluate(&threshold, &series, now).await.is_err());
+    }
+}
@@ -0,0 +1,426 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use super::{
+    AggregationType, MetricDataPoint, MetricDefinition, MetricQuery, MetricType, MetricUnit, MetricValue, MetricsManager,
+};
+use crate::error::{ObservabilityError, ObservabilityResult};
+
+pub const METRICS_NAMESPACE: &str = "Sirsi/Observability";
+pub const ACTIVE_SERIES_METRIC: &str = "metric_series_active";
+pub const OVER_LIMIT_METRIC: &str = "metric_points_over_limit";
+pub const DIM_NAMESPACE: &str = "namespace";
+pub const DIM_POLICY: &str = "policy";
+
+// Dimension key/value that overflowed points are folded into under `OverflowPolicy::Aggregate`
+pub const OVERFLOW_DIMENSION: &str = "__overflow__";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowPolicy {
+    // Points for new series are dropped and the write returns a validation error
+    Reject,
+    // Points for new series lose their dimensions and land in one overflow series per metric
+    Aggregate,
+}
+
+impl OverflowPolicy {
+    fn as_str(&self) -> &'static str {
+        match self {
+            OverflowPolicy::Reject => "reject",
+            OverflowPolicy::Aggregate => "aggregate",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CardinalityLimits {
+    pub default_max_series: usize,
+    pub namespaces: HashMap<String, usize>,
+    pub policy: OverflowPolicy,
+    // At most one over-limit warning per namespace per interval
+    pub warning_interval: Duration,
+}
+
+impl Default for CardinalityLimits {
+    fn default() -> Self {
+        Self {
+            default_max_series: 10_000,
+            namespaces: HashMap::new(),
+            policy: OverflowPolicy::Reject,
+            warning_interval: Duration::from_secs(60),
+        }
+    }
+}
+
+impl CardinalityLimits {
+    pub fn with_namespace_limit(mut self, namespace: impl Into<String>, max_series: usize) -> Self {
+        self.namespaces.insert(namespace.into(), max_series);
+        self
+    }
+
+    pub fn with_policy(mut self, policy: OverflowPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    fn max_series(&self, namespace: &str) -> usize {
+        self.namespaces.get(namespace).copied().unwrap_or(self.default_max_series)
+    }
+}
+
+const HLL_PRECISION: u32 = 12;
+const HLL_REGISTERS: usize = 1 << HLL_PRECISION;
+
+// HyperLogLog distinct counter, about 1.6% standard error in 4 KiB
+#[derive(Clone)]
+struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+impl HyperLogLog {
+    fn new() -> Self {
+        Self { registers: vec![0; HLL_REGISTERS] }
+    }
+
+    fn insert(&mut self, hash: u64) {
+        let index = (hash >> (64 - HLL_PRECISION)) as usize;
+        let rank = ((hash << HLL_PRECISION) | (1 << (HLL_PRECISION - 1))).leading_zeros() as u8 + 1;
+        self.registers[index] = self.registers[index].max(rank);
+    }
+
+    fn estimate(&self) -> u64 {
+        let m = HLL_REGISTERS as f64;
+        let sum: f64 = self.registers.iter().map(|&r| 2f64.powi(-i32::from(r))).sum();
+        let raw = 0.7213 / (1.0 + 1.079 / m) * m * m / sum;
+        let zeros = self.registers.iter().filter(|&&r| r == 0).count();
+        // Linear counting is far more accurate while most registers are still empty
+        let estimate = if raw <= 2.5 * m && zeros > 0 { m * (m / zeros as f64).ln() } else { raw };
+        estimate.round() as u64
+    }
+}
+
+fn hash_of(value: impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn series_hash(point: &MetricDataPoint) -> u64 {
+    let dimensions: BTreeMap<_, _> = point.dimensions.iter().collect();
+    hash_of((&point.name, &dimensions))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DimensionCardinality {
+    pub dimension: String,
+    pub estimated_values: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CardinalityReport {
+    pub namespace: String,
+    pub max_series: usize,
+    pub active_series: usize,
+    // Distinct series ever written, including the ones turned away
+    pub estimated_series: u64,
+    pub over_limit_points: u64,
+    // Highest estimated distinct values first
+    pub dimensions: Vec<DimensionCardinality>,
+}
+
+struct NamespaceState {
+    // Admitted series; bounded by the namespace limit
+    series: HashSet<u64>,
+    attempted: HyperLogLog,
+    dimensions: HashMap<String, HyperLogLog>,
+    over_limit: u64,
+    reported_over_limit: u64,
+    last_warning: Option<Instant>,
+}
+
+impl NamespaceState {
+    fn new() -> Self {
+        Self {
+            series: HashSet::new(),
+            attempted: HyperLogLog::new(),
+            dimensions: HashMap::new(),
+            over_limit: 0,
+            reported_over_limit: 0,
+            last_warning: None,
+        }
+    }
+}
+
+// Wraps a metrics backend with per-namespace series limits. Series already admitted always keep
+// ingesting; only series first seen after the limit is reached are rejected or folded into the
+// overflow series. Limits can be swapped at runtime with `set_limits`.
+pub struct CardinalityGuard {
+    inner: Arc<dyn MetricsManager>,
+    limits: RwLock<CardinalityLimits>,
+    namespaces: Mutex<HashMap<String, NamespaceState>>,
+}
+
+impl CardinalityGuard {
+    pub fn new(inner: Arc<dyn MetricsManager>, limits: CardinalityLimits) -> Self {
+        Self { inner, limits: RwLock::new(limits), namespaces: Mutex::new(HashMap::new()) }
+    }
+
+    // Lowering a limit below the current count leaves existing series alone and stops new ones
+    pub fn set_limits(&self, limits: CardinalityLimits) {
+        *self.limits.write().expect("cardinality limits lock poisoned") = limits;
+    }
+
+    pub fn limits(&self) -> CardinalityLimits {
+        self.limits.read().expect("cardinality limits lock poisoned").clone()
+    }
+
+    pub fn top_cardinality(&self, namespace: &str) -> ObservabilityResult<CardinalityReport> {
+        let max_series = self.limits().max_series(namespace);
+        let namespaces = self.namespaces.lock().expect("cardinality state lock poisoned");
+        let state = namespaces
+            .get(namespace)
+            .ok_or_else(|| ObservabilityError::NotFound(format!("No metrics written to namespace {}", namespace)))?;
+        let mut dimensions: Vec<DimensionCardinality> = state
+            .dimensions
+            .iter()
+            .map(|(dimension, hll)| DimensionCardinality { dimension: dimension.clone(), estimated_values: hll.estimate() })
+            .collect();
+        dimensions.sort_by(|a, b| b.estimated_values.cmp(&a.estimated_values).then_with(|| a.dimension.cmp(&b.dimension)));
+        Ok(CardinalityReport {
+            namespace: namespace.to_string(),
+            max_series,
+            active_series: state.series.len(),
+            estimated_series: state.attempted.estimate(),
+            over_limit_points: state.over_limit,
+            dimensions,
+        })
+    }
+
+    // Points for this guard's own metrics; report them through a manager that isn't guarded by
+    // the same limits
+    pub fn metric_points(&self, now: DateTime<Utc>) -> Vec<MetricDataPoint> {
+        let policy = self.limits().policy;
+        let mut namespaces = self.namespaces.lock().expect("cardinality state lock poisoned");
+        let mut points = Vec::new();
+        for (namespace, state) in namespaces.iter_mut() {
+            let over_limit = state.over_limit - state.reported_over_limit;
+            state.reported_over_limit = state.over_limit;
+            let dimensions = HashMap::from([
+                (DIM_NAMESPACE.to_string(), namespace.clone()),
+                (DIM_POLICY.to_string(), policy.as_str().to_string()),
+            ]);
+            for (name, value) in [(ACTIVE_SERIES_METRIC, state.series.len() as f64), (OVER_LIMIT_METRIC, over_limit as f64)] {
+                points.push(MetricDataPoint {
+                    name: name.to_string(),
+                    namespace: METRICS_NAMESPACE.to_string(),
+                    dimensions: dimensions.clone(),
+                    timestamp: now,
+                    value: MetricValue::Single(value),
+                    exemplars: Vec::new(),
+                });
+            }
+        }
+        points
+    }
+
+    // Splits a batch into points to forward (possibly rewritten) and the number rejected
+    fn admit(&self, data_points: Vec<MetricDataPoint>) -> (Vec<MetricDataPoint>, HashMap<String, usize>) {
+        let limits = self.limits();
+        let mut namespaces = self.namespaces.lock().expect("cardinality state lock poisoned");
+        let mut admitted = Vec::with_capacity(data_points.len());
+        let mut rejected: HashMap<String, usize> = HashMap::new();
+        for mut point in data_points {
+            let state = namespaces.entry(point.namespace.clone()).or_insert_with(NamespaceState::new);
+            let hash = series_hash(&point);
+            state.attempted.insert(hash);
+            for (key, value) in &point.dimensions {
+                state.dimensions.entry(key.clone()).or_insert_with(HyperLogLog::new).insert(hash_of(value));
+            }
+
+            let max_series = limits.max_series(&point.namespace);
+            if state.series.contains(&hash) || state.series.len() < max_series {
+                state.series.insert(hash);
+                admitted.push(point);
+                continue;
+            }
+
+            state.over_limit += 1;
+            let warn_now = state.last_warning.is_none_or(|at| at.elapsed() >= limits.warning_interval);
+            if warn_now {
+                state.last_warning = Some(Instant::now());
+                warn!(
+                    "Metrics namespace {} is at its limit of {} series; new series are {}",
+                    point.namespace,
+                    max_series,
+                    match limits.policy {
+                        OverflowPolicy::Reject => "rejected",
+                        OverflowPolicy::Aggregate => "aggregated",
+                    }
+                );
+            }
+            match limits.policy {
+                OverflowPolicy::Reject => *rejected.entry(point.namespace).or_default() += 1,
+                OverflowPolicy::Aggregate => {
+                    point.dimensions = HashMap::from([(OVERFLOW_DIMENSION.to_string(), "true".to_string())]);
+                    admitted.push(point);
+                }
+            }
+        }
+        (admitted, rejected)
+    }
+}
+
+#[async_trait]
+impl MetricsManager for CardinalityGuard {
+    async fn register_metric(&self, definition: MetricDefinition) -> ObservabilityResult<()> {
+        self.inner.register_metric(definition).await
+    }
+
+    // Admitted points are written even when part of the batch is rejected; the error says how many
+    // were dropped, and isn't retryable
+    async fn put_metric_data(&self, data_points: Vec<MetricDataPoint>) -> ObservabilityResult<()> {
+        let (admitted, rejected) = self.admit(data_points);
+        if !admitted.is_empty() {
+            self.inner.put_metric_data(admitted).await?;
+        }
+        if rejected.is_empty() {
+            return Ok(());
+        }
+        let mut rejected: Vec<_> = rejected.into_iter().collect();
+        rejected.sort();
+        let summary: Vec<String> = rejected.iter().map(|(namespace, count)| format!("{} in {}", count, namespace)).collect();
+        Err(ObservabilityError::Validation(format!(
+            "Rejected points for new series over the cardinality limit: {}",
+            summary.join(", ")
+        )))
+    }
+
+    async fn get_metric_data(&self, query: MetricQuery) -> ObservabilityResult<Vec<MetricDataPoint>> {
+        self.inner.get_metric_data(query).await
+    }
+
+    async fn list_metrics(&self, namespace: Option<String>) -> ObservabilityResult<Vec<MetricDefinition>> {
+        self.inner.list_metrics(namespace).await
+    }
+
+    async fn delete_metric(&self, name: &str, namespace: &str) -> ObservabilityResult<()> {
+        self.inner.delete_metric(name, namespace).await
+    }
+}
+
+pub fn metric_definitions() -> Vec<MetricDefinition> {
+    let definition = |name: &str, metric_type: MetricType, aggregations: Vec<AggregationType>| MetricDefinition {
+        name: name.to_string(),
+        namespace: METRICS_NAMESPACE.to_string(),
+        metric_type,
+        unit: MetricUnit::Count,
+        dimensions: vec![DIM_NAMESPACE.to_string(), DIM_POLICY.to_string()],
+        aggregations,
+        retention_days: 30,
+    };
+    vec![
+        definition(ACTIVE_SERIES_METRIC, MetricType::Gauge, vec![AggregationType::Maximum]),
+        definition(OVER_LIMIT_METRIC, MetricType::Counter, vec![AggregationType::Sum]),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct RecordingMetrics {
+        points: Mutex<Vec<MetricDataPoint>>,
+    }
+
+    #[async_trait]
+    impl MetricsManager for RecordingMetrics {
+        async fn register_metric(&self, _definition: MetricDefinition) -> ObservabilityResult<()> {
+            Ok(())
+        }
+        async fn put_metric_data(&self, data_points: Vec<MetricDataPoint>) -> ObservabilityResult<()> {
+            self.points.lock().unwrap().extend(data_points);
+            Ok(())
+        }
+        async fn get_metric_data(&self, _query: MetricQuery) -> ObservabilityResult<Vec<MetricDataPoint>> {
+            Ok(self.points.lock().unwrap().clone())
+        }
+        async fn list_metrics(&self, _namespace: Option<String>) -> ObservabilityResult<Vec<MetricDefinition>> {
+            Ok(Vec::new())
+        }
+        async fn delete_metric(&self, _name: &str, _namespace: &str) -> ObservabilityResult<()> {
+            Ok(())
+        }
+    }
+
+    fn point(request_id: usize) -> MetricDataPoint {
+        MetricDataPoint {
+            name: "latency_ms".to_string(),
+            namespace: "Sirsi/Checkout".to_string(),
+            dimensions: HashMap::from([
+                ("route".to_string(), "/cart".to_string()),
+                ("request_id".to_string(), format!("req-{}", request_id)),
+            ]),
+            timestamp: Utc::now(),
+            value: MetricValue::Single(12.0),
+            exemplars: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_limit_rejects_new_series_but_not_existing_ones() {
+        let inner = Arc::new(RecordingMetrics::default());
+        let guard = CardinalityGuard::new(inner.clone(), CardinalityLimits::default().with_namespace_limit("Sirsi/Checkout", 3));
+
+        guard.put_metric_data((0..3).map(point).collect()).await.unwrap();
+        let err = guard.put_metric_data(vec![point(3), point(4)]).await.unwrap_err();
+        assert!(matches!(err, ObservabilityError::Validation(ref msg) if msg.contains("2 in Sirsi/Checkout")));
+
+        // Known series still get through, even alongside rejected ones
+        assert!(guard.put_metric_data(vec![point(1), point(5)]).await.is_err());
+        guard.put_metric_data(vec![point(0), point(2)]).await.unwrap();
+        assert_eq!(inner.points.lock().unwrap().len(), 6);
+
+        let report = guard.top_cardinality("Sirsi/Checkout").unwrap();
+        assert_eq!((report.active_series, report.max_series, report.over_limit_points), (3, 3, 3));
+        assert_eq!(report.estimated_series, 6);
+        assert_eq!(report.dimensions[0].dimension, "request_id");
+        assert_eq!(report.dimensions[0].estimated_values, 6);
+        assert_eq!(report.dimensions[1].estimated_values, 1);
+
+        // Raising the limit at runtime lets the next new series in
+        guard.set_limits(CardinalityLimits::default().with_namespace_limit("Sirsi/Checkout", 4));
+        guard.put_metric_data(vec![point(3)]).await.unwrap();
+        assert!(guard.put_metric_data(vec![point(4)]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_aggregate_policy_folds_new_series_into_overflow() {
+        let inner = Arc::new(RecordingMetrics::default());
+        let limits = CardinalityLimits::default().with_namespace_limit("Sirsi/Checkout", 2).with_policy(OverflowPolicy::Aggregate);
+        let guard = CardinalityGuard::new(inner.clone(), limits);
+
+        guard.put_metric_data((0..10).map(point).collect()).await.unwrap();
+        let points = inner.points.lock().unwrap().clone();
+        assert_eq!(points.len(), 10);
+        let overflow = points.iter().filter(|p| p.dimensions.contains_key(OVERFLOW_DIMENSION)).count();
+        assert_eq!(overflow, 8);
+        assert!(points[..2].iter().all(|p| p.dimensions.contains_key("request_id")));
+
+        let metrics = guard.metric_points(Utc::now());
+        let over_limit = metrics.iter().find(|p| p.name == OVER_LIMIT_METRIC).unwrap();
+        assert!(matches!(over_limit.value, MetricValue::Single(v) if v == 8.0));
+        assert_eq!(over_limit.dimensions[DIM_POLICY], "aggregate");
+        // Counters are reported as deltas
+        let again = guard.metric_points(Utc::now());
+        assert!(matches!(again.iter().find(|p| p.name == OVER_LIMIT_METRIC).unwrap().value, MetricValue::Single(v) if v == 0.0));
+    }
+}
@@ -0,0 +1,425 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+use super::{Exemplar, MetricDataPoint, MetricDefinition, MetricQuery, MetricsManager};
+use crate::error::ObservabilityResult;
+use crate::tracing::{AttributeValue, ServiceDependency, ServiceMap, Span, Trace, TraceQuery, TracingManager};
+
+pub const EXEMPLAR_METRIC_ATTRIBUTE: &str = "exemplar.metric";
+pub const EXEMPLAR_VALUE_ATTRIBUTE: &str = "exemplar.value";
+
+#[derive(Debug, Clone)]
+pub struct ExemplarConfig {
+    // Exemplars are bucketed per series into windows of this length
+    pub window: Duration,
+    pub max_per_window: usize,
+    // Hard cap across every series; the oldest exemplars go first
+    pub max_total: usize,
+}
+
+impl Default for ExemplarConfig {
+    fn default() -> Self {
+        Self { window: Duration::from_secs(60), max_per_window: 4, max_total: 50_000 }
+    }
+}
+
+struct SeriesExemplars {
+    dimensions: HashMap<String, String>,
+    // Window start (unix seconds) to exemplars, oldest first
+    windows: BTreeMap<i64, VecDeque<Exemplar>>,
+}
+
+#[derive(Default)]
+struct Index {
+    series: HashMap<String, SeriesExemplars>,
+    // (window start, series key) for every non-empty window, so global eviction finds the oldest
+    by_age: BTreeSet<(i64, String)>,
+    // (trace id, span id) to the metric the exemplar was recorded for
+    spans: HashMap<(String, String), (String, f64)>,
+    total: usize,
+}
+
+fn series_key(namespace: &str, name: &str, dimensions: &HashMap<String, String>) -> String {
+    let sorted: BTreeMap<_, _> = dimensions.iter().collect();
+    let parts: Vec<String> = sorted.into_iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+    format!("{}/{}{{{}}}", namespace, name, parts.join(","))
+}
+
+fn metric_prefix(namespace: &str, name: &str) -> String {
+    format!("{}/{}{{", namespace, name)
+}
+
+impl Index {
+    fn forget(&mut self, exemplar: &Exemplar) {
+        self.spans.remove(&(exemplar.trace_id.clone(), exemplar.span_id.clone()));
+        self.total -= 1;
+    }
+
+    // Drops the oldest exemplar of one window, and the window itself once it's empty
+    fn evict_from(&mut self, key: &str, window: i64) {
+        let Some(series) = self.series.get_mut(key) else { return };
+        let Some(exemplars) = series.windows.get_mut(&window) else { return };
+        let evicted = exemplars.pop_front();
+        if exemplars.is_empty() {
+            series.windows.remove(&window);
+            self.by_age.remove(&(window, key.to_string()));
+            if series.windows.is_empty() {
+                self.series.remove(key);
+            }
+        }
+        if let Some(evicted) = evicted {
+            self.forget(&evicted);
+        }
+    }
+}
+
+// Bounded exemplar storage shared by the metrics recorder and the trace annotator
+pub struct ExemplarStore {
+    config: ExemplarConfig,
+    index: RwLock<Index>,
+}
+
+impl Default for ExemplarStore {
+    fn default() -> Self {
+        Self::new(ExemplarConfig::default())
+    }
+}
+
+impl ExemplarStore {
+    pub fn new(config: ExemplarConfig) -> Self {
+        Self { config, index: RwLock::new(Index::default()) }
+    }
+
+    fn window_start(&self, at: DateTime<Utc>) -> i64 {
+        let window = self.config.window.as_secs().max(1) as i64;
+        at.timestamp().div_euclid(window) * window
+    }
+
+    pub fn record(&self, point: &MetricDataPoint) {
+        if point.exemplars.is_empty() {
+            return;
+        }
+        let key = series_key(&point.namespace, &point.name, &point.dimensions);
+        let metric = format!("{}/{}", point.namespace, point.name);
+        let mut index = self.index.write().expect("exemplar index lock poisoned");
+        for exemplar in &point.exemplars {
+            let window = self.window_start(exemplar.timestamp);
+            let series = index
+                .series
+                .entry(key.clone())
+                .or_insert_with(|| SeriesExemplars { dimensions: point.dimensions.clone(), windows: BTreeMap::new() });
+            let exemplars = series.windows.entry(window).or_default();
+            let position = exemplars.partition_point(|e| e.timestamp <= exemplar.timestamp);
+            exemplars.insert(position, exemplar.clone());
+            let over = exemplars.len() > self.config.max_per_window;
+            index.by_age.insert((window, key.clone()));
+            index.spans.insert((exemplar.trace_id.clone(), exemplar.span_id.clone()), (metric.clone(), exemplar.value));
+            index.total += 1;
+            if over {
+                index.evict_from(&key, window);
+            }
+        }
+        while index.total > self.config.max_total {
+            let Some((window, oldest)) = index.by_age.first().cloned() else { break };
+            index.evict_from(&oldest, window);
+        }
+    }
+
+    // Exemplars in [start, end) for every series of the metric whose dimensions include `filter`,
+    // oldest first
+    pub fn query(
+        &self,
+        namespace: &str,
+        name: &str,
+        filter: &HashMap<String, String>,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Vec<Exemplar> {
+        let prefix = metric_prefix(namespace, name);
+        let (first, last) = (self.window_start(start), self.window_start(end));
+        let index = self.index.read().expect("exemplar index lock poisoned");
+        let mut found: Vec<Exemplar> = index
+            .series
+            .iter()
+            .filter(|(key, series)| {
+                key.starts_with(&prefix) && filter.iter().all(|(k, v)| series.dimensions.get(k) == Some(v))
+            })
+            .flat_map(|(_, series)| series.windows.range(first..=last).flat_map(|(_, exemplars)| exemplars.iter()))
+            .filter(|e| e.timestamp >= start && e.timestamp < end)
+            .cloned()
+            .collect();
+        found.sort_by(|a, b| a.timestamp.cmp(&b.timestamp).then_with(|| a.span_id.cmp(&b.span_id)));
+        found
+    }
+
+    pub fn len(&self) -> usize {
+        self.index.read().expect("exemplar index lock poisoned").total
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Marks spans that back a stored exemplar so the trace view can link back to the metric
+    pub fn annotate(&self, spans: &mut [Span]) {
+        let index = self.index.read().expect("exemplar index lock poisoned");
+        for span in spans {
+            if let Some((metric, value)) = index.spans.get(&(span.trace_id.clone(), span.span_id.clone())) {
+                span.attributes.insert(EXEMPLAR_METRIC_ATTRIBUTE.to_string(), AttributeValue::String(metric.clone()));
+                span.attributes.insert(EXEMPLAR_VALUE_ATTRIBUTE.to_string(), AttributeValue::Float(*value));
+            }
+        }
+    }
+}
+
+// Takes exemplars off incoming points into the store and attaches them to query results when
+// `include_exemplars` is set, so the backend underneath never sees them
+pub struct ExemplarRecorder {
+    inner: Arc<dyn MetricsManager>,
+    store: Arc<ExemplarStore>,
+}
+
+impl ExemplarRecorder {
+    pub fn new(inner: Arc<dyn MetricsManager>, store: Arc<ExemplarStore>) -> Self {
+        Self { inner, store }
+    }
+}
+
+#[async_trait]
+impl MetricsManager for ExemplarRecorder {
+    async fn register_metric(&self, definition: MetricDefinition) -> ObservabilityResult<()> {
+        self.inner.register_metric(definition).await
+    }
+
+    async fn put_metric_data(&self, mut data_points: Vec<MetricDataPoint>) -> ObservabilityResult<()> {
+        for point in &mut data_points {
+            self.store.record(point);
+            point.exemplars.clear();
+        }
+        self.inner.put_metric_data(data_points).await
+    }
+
+    // Each aggregated point gets the newest exemplars from its own period
+    async fn get_metric_data(&self, query: MetricQuery) -> ObservabilityResult<Vec<MetricDataPoint>> {
+        let include = query.include_exemplars;
+        let (namespace, name, filter, period) = (
+            query.namespace.clone(),
+            query.metric_name.clone(),
+            query.dimensions.clone().unwrap_or_default(),
+            chrono::Duration::seconds(i64::from(query.period.max(1))),
+        );
+        let mut points = self.inner.get_metric_data(query).await?;
+        for point in &mut points {
+            point.exemplars.clear();
+            if !include {
+                continue;
+            }
+            let mut dimensions = filter.clone();
+            dimensions.extend(point.dimensions.iter().map(|(k, v)| (k.clone(), v.clone())));
+            let mut exemplars = self.store.query(&namespace, &name, &dimensions, point.timestamp, point.timestamp + period);
+            let skip = exemplars.len().saturating_sub(self.store.config.max_per_window);
+            point.exemplars = exemplars.split_off(skip);
+        }
+        Ok(points)
+    }
+
+    async fn list_metrics(&self, namespace: Option<String>) -> ObservabilityResult<Vec<MetricDefinition>> {
+        self.inner.list_metrics(namespace).await
+    }
+
+    async fn delete_metric(&self, name: &str, namespace: &str) -> ObservabilityResult<()> {
+        self.inner.delete_metric(name, namespace).await
+    }
+}
+
+// Annotates spans with the exemplar they back on the way out. Spans usually arrive before the
+// metric flush that records their exemplar, so this happens at read time rather than on ingest.
+pub struct ExemplarLinkedTracing {
+    inner: Arc<dyn TracingManager>,
+    store: Arc<ExemplarStore>,
+}
+
+impl ExemplarLinkedTracing {
+    pub fn new(inner: Arc<dyn TracingManager>, store: Arc<ExemplarStore>) -> Self {
+        Self { inner, store }
+    }
+}
+
+#[async_trait]
+impl TracingManager for ExemplarLinkedTracing {
+    async fn store_trace(&self, trace: Trace) -> ObservabilityResult<String> {
+        self.inner.store_trace(trace).await
+    }
+
+    async fn get_trace(&self, trace_id: &str) -> ObservabilityResult<Trace> {
+        let mut trace = self.inner.get_trace(trace_id).await?;
+        self.store.annotate(&mut trace.spans);
+        Ok(trace)
+    }
+
+    async fn search_traces(&self, query: TraceQuery) -> ObservabilityResult<Vec<Trace>> {
+        let mut traces = self.inner.search_traces(query).await?;
+        for trace in &mut traces {
+            self.store.annotate(&mut trace.spans);
+        }
+        Ok(traces)
+    }
+
+    async fn get_service_map(&self, window: Duration) -> ObservabilityResult<ServiceMap> {
+        self.inner.get_service_map(window).await
+    }
+
+    async fn get_dependencies(&self, service_name: &str) -> ObservabilityResult<Vec<ServiceDependency>> {
+        self.inner.get_dependencies(service_name).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{AggregationType, MetricValue};
+    use crate::tracing::{SpanKind, SpanStatus};
+    use chrono::TimeZone;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct RecordingMetrics {
+        points: Mutex<Vec<MetricDataPoint>>,
+    }
+
+    #[async_trait]
+    impl MetricsManager for RecordingMetrics {
+        async fn register_metric(&self, _definition: MetricDefinition) -> ObservabilityResult<()> {
+            Ok(())
+        }
+        async fn put_metric_data(&self, data_points: Vec<MetricDataPoint>) -> ObservabilityResult<()> {
+            self.points.lock().unwrap().extend(data_points);
+            Ok(())
+        }
+        async fn get_metric_data(&self, query: MetricQuery) -> ObservabilityResult<Vec<MetricDataPoint>> {
+            let points = self.points.lock().unwrap();
+            Ok(points
+                .iter()
+                .filter(|p| p.timestamp >= query.start_time && p.timestamp < query.end_time)
+                .cloned()
+                .collect())
+        }
+        async fn list_metrics(&self, _namespace: Option<String>) -> ObservabilityResult<Vec<MetricDefinition>> {
+            Ok(Vec::new())
+        }
+        async fn delete_metric(&self, _name: &str, _namespace: &str) -> ObservabilityResult<()> {
+            Ok(())
+        }
+    }
+
+    fn t(seconds: i64) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap() + chrono::Duration::seconds(seconds)
+    }
+
+    fn exemplar(span: &str, value: f64, at: DateTime<Utc>) -> Exemplar {
+        Exemplar { trace_id: format!("trace-{}", span), span_id: span.to_string(), value, timestamp: at }
+    }
+
+    fn histogram(route: &str, at: DateTime<Utc>, exemplars: Vec<Exemplar>) -> MetricDataPoint {
+        MetricDataPoint {
+            name: "http_latency_ms".to_string(),
+            namespace: "Sirsi/Gateway".to_string(),
+            dimensions: HashMap::from([("route".to_string(), route.to_string())]),
+            timestamp: at,
+            value: MetricValue::Distribution { sum: 900.0, count: 10, min: 20.0, max: 400.0 },
+            exemplars,
+        }
+    }
+
+    fn query(include_exemplars: bool) -> MetricQuery {
+        MetricQuery {
+            metric_name: "http_latency_ms".to_string(),
+            namespace: "Sirsi/Gateway".to_string(),
+            dimensions: None,
+            aggregation: AggregationType::Percentile(99.0),
+            period: 60,
+            start_time: t(0),
+            end_time: t(180),
+            include_exemplars,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_exemplars_come_back_with_their_period() {
+        let inner = Arc::new(RecordingMetrics::default());
+        let store = Arc::new(ExemplarStore::default());
+        let metrics = ExemplarRecorder::new(inner.clone(), store.clone());
+
+        metrics
+            .put_metric_data(vec![
+                histogram("/cart", t(0), vec![exemplar("a", 380.0, t(12)), exemplar("b", 400.0, t(41))]),
+                histogram("/cart", t(60), vec![exemplar("c", 350.0, t(75))]),
+                histogram("/cart", t(120), Vec::new()),
+                histogram("/login", t(0), vec![exemplar("d", 90.0, t(3))]),
+            ])
+            .await
+            .unwrap();
+        // The backend never stores exemplars itself
+        assert!(inner.points.lock().unwrap().iter().all(|p| p.exemplars.is_empty()));
+
+        let points = metrics.get_metric_data(query(true)).await.unwrap();
+        let spans = |route: &str, at: DateTime<Utc>| -> Vec<String> {
+            let point = points.iter().find(|p| p.dimensions["route"] == route && p.timestamp == at).unwrap();
+            point.exemplars.iter().map(|e| e.span_id.clone()).collect()
+        };
+        assert_eq!(spans("/cart", t(0)), vec!["a", "b"]);
+        assert_eq!(spans("/cart", t(60)), vec!["c"]);
+        assert!(spans("/cart", t(120)).is_empty());
+        assert_eq!(spans("/login", t(0)), vec!["d"]);
+
+        let points = metrics.get_metric_data(query(false)).await.unwrap();
+        assert!(points.iter().all(|p| p.exemplars.is_empty()));
+    }
+
+    #[test]
+    fn test_caps_evict_oldest_exemplars() {
+        let store = ExemplarStore::new(ExemplarConfig { window: Duration::from_secs(60), max_per_window: 2, max_total: 4 });
+
+        // Three in one window: the earliest is dropped, even when it arrives last
+        store.record(&histogram("/cart", t(0), vec![exemplar("a2", 1.0, t(20)), exemplar("a3", 1.0, t(30))]));
+        store.record(&histogram("/cart", t(0), vec![exemplar("a1", 1.0, t(10))]));
+        let filter = HashMap::new();
+        let kept: Vec<_> =
+            store.query("Sirsi/Gateway", "http_latency_ms", &filter, t(0), t(60)).into_iter().map(|e| e.span_id).collect();
+        assert_eq!(kept, vec!["a2", "a3"]);
+
+        // Past the global cap the oldest window across all series goes first
+        store.record(&histogram("/login", t(60), vec![exemplar("b1", 1.0, t(61)), exemplar("b2", 1.0, t(62))]));
+        store.record(&histogram("/cart", t(120), vec![exemplar("c1", 1.0, t(121))]));
+        assert_eq!(store.len(), 4);
+        let kept: Vec<_> =
+            store.query("Sirsi/Gateway", "http_latency_ms", &filter, t(0), t(180)).into_iter().map(|e| e.span_id).collect();
+        assert_eq!(kept, vec!["a3", "b1", "b2", "c1"]);
+
+        // Evicted exemplars no longer annotate their spans
+        let span = |id: &str| Span {
+            span_id: id.to_string(),
+            trace_id: format!("trace-{}", id),
+            parent_span_id: None,
+            name: "GET /cart".to_string(),
+            kind: SpanKind::Server,
+            start_time: t(0),
+            end_time: t(1),
+            attributes: HashMap::new(),
+            events: Vec::new(),
+            links: Vec::new(),
+            status: SpanStatus::Ok,
+        };
+        let mut spans = vec![span("a2"), span("b1")];
+        store.annotate(&mut spans);
+        assert!(!spans[0].attributes.contains_key(EXEMPLAR_METRIC_ATTRIBUTE));
+        assert!(matches!(
+            &spans[1].attributes[EXEMPLAR_METRIC_ATTRIBUTE],
+            AttributeValue::String(metric) if metric == "Sirsi/Gateway/http_latency_ms"
+        ));
+    }
+}
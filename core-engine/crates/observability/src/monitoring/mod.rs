@@ -5,6 +5,24 @@ use chrono::{DateTime, Utc};
 
 use crate::error::ObservabilityResult;
 
+pub mod absence;
+pub mod anomaly;
+pub mod cardinality;
+pub mod exemplar;
+pub mod runbook;
+pub mod silence;
+pub mod slo;
+pub mod webhook;
+
+pub use absence::{AbsenceConfig, AbsenceDetector, AbsenceEvaluation, SeriesAbsence};
+pub use anomaly::{AnomalyConfig, AnomalyDetector, AnomalyEvaluation, BaselineKind};
+pub use cardinality::{CardinalityGuard, CardinalityLimits, CardinalityReport, OverflowPolicy};
+pub use exemplar::{ExemplarConfig, ExemplarLinkedTracing, ExemplarRecorder, ExemplarStore};
+pub use runbook::{ActionRun, ActionStatus, AlertAction, AlertActions, ExecutionMode, ParameterBinding, RunbookExecutor, WorkflowLauncher};
+pub use silence::{AlertDispatcher, Silence, SilenceManager, SilenceMatcher};
+pub use slo::{SloDefinition, SloIndicator, SloManager, SloStatus, SloStatusReport};
+pub use webhook::{DeliveryStatus, WebhookDelivery, WebhookSender};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MetricDefinition {
     pub name: String,
@@ -70,6 +88,18 @@ pub struct MetricDataPoint {
     pub dimensions: HashMap<String, String>,
     pub timestamp: DateTime<Utc>,
     pub value: MetricValue,
+    // Sample traces behind a histogram point; on query results, only filled when
+    // `include_exemplars` was set
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exemplars: Vec<Exemplar>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Exemplar {
+    pub trace_id: String,
+    pub span_id: String,
+    pub value: f64,
+    pub timestamp: DateTime<Utc>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -88,6 +118,8 @@ pub struct MetricQuery {
     pub period: i32,
     pub start_time: DateTime<Utc>,
     pub end_time: DateTime<Utc>,
+    #[serde(default)]
+    pub include_exemplars: bool,
 }
 
 #[async_trait]
@@ -177,9 +209,12 @@ pub struct AlertRule {
     pub notification_channels: Vec<NotificationChannel>,
     pub evaluation_interval: i32,
     pub enabled: bool,
+    // Runbook workflows to start, or offer, when the rule fires
+    #[serde(default)]
+    pub alert_actions: AlertActions,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum AlertSeverity {
     Critical,
     Error,
@@ -199,6 +234,11 @@ pub enum AlertCondition {
         sensitivity: f64,
         duration_seconds: i32,
     },
+    // Fires when a series matching the query hasn't reported for interval + grace
+    Absence {
+        expected_interval_seconds: i32,
+        grace_seconds: i32,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -215,6 +255,8 @@ pub enum ComparisonOperator {
 pub enum DeviationType {
     StandardDeviation,
     PercentageChange,
+    // Hour-of-week median baseline; sensitivity is measured in MADs
+    Seasonal,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -246,6 +288,8 @@ pub struct AlertEvent {
     pub timestamp: DateTime<Utc>,
     pub resolved_at: Option<DateTime<Utc>>,
     pub metadata: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub actions: Vec<ActionRun>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -263,6 +307,9 @@ pub trait AlertManager: Send + Sync {
     async fn get_alert_rule(&self, id: &str) -> ObservabilityResult<AlertRule>;
     async fn list_alert_rules(&self) -> ObservabilityResult<Vec<AlertRule>>;
     async fn get_alert_events(&self, rule_id: Option<String>) -> ObservabilityResult<Vec<AlertEvent>>;
+    async fn record_alert_event(&self, event: AlertEvent) -> ObservabilityResult<AlertEvent>;
+    async fn list_webhook_deliveries(&self, channel_id: &str) -> ObservabilityResult<Vec<WebhookDelivery>>;
+    async fn redeliver(&self, delivery_id: &str) -> ObservabilityResult<WebhookDelivery>;
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
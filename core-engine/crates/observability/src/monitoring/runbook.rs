@@ -0,0 +1,488 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use super::{AlertEvent, AlertRule, AlertState};
+use crate::error::{ObservabilityError, ObservabilityResult};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertActions {
+    pub workflows: Vec<AlertAction>,
+    // Runs of this rule's workflows in flight at once, manual ones included
+    pub max_concurrent_runs: usize,
+}
+
+impl Default for AlertActions {
+    fn default() -> Self {
+        Self { workflows: Vec::new(), max_concurrent_runs: 1 }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertAction {
+    pub id: String,
+    // Automation workflow template to start
+    pub workflow: String,
+    // Workflow parameter name to where its value comes from
+    pub parameters: HashMap<String, ParameterBinding>,
+    pub mode: ExecutionMode,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ParameterBinding {
+    // An alert event metadata key, e.g. the resource id
+    Metadata(String),
+    // A dimension of the rule's query, falling back to the event metadata for per-series alerts
+    Dimension(String),
+    Literal(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ExecutionMode {
+    // Recorded as pending until someone starts it from the alert
+    Manual,
+    // Started on firing, at most once per cooldown so a flapping alert doesn't stampede
+    Automatic { cooldown_seconds: i64 },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ActionStatus {
+    Pending,
+    Skipped,
+    Running,
+    Succeeded,
+    Failed,
+}
+
+impl ActionStatus {
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, ActionStatus::Skipped | ActionStatus::Succeeded | ActionStatus::Failed)
+    }
+}
+
+// One attempted (or offered) runbook execution, shown on the alert event's timeline
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActionRun {
+    pub id: String,
+    pub action_id: String,
+    pub workflow: String,
+    pub parameters: HashMap<String, String>,
+    pub status: ActionStatus,
+    pub workflow_run_id: Option<String>,
+    // Why it was skipped or how it failed
+    pub detail: Option<String>,
+    pub started_by: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WorkflowRunStatus {
+    Running,
+    Succeeded,
+    Failed(String),
+}
+
+#[async_trait]
+pub trait WorkflowLauncher: Send + Sync {
+    // Returns the workflow run id; the key makes retried starts idempotent
+    async fn start(&self, workflow: &str, parameters: &HashMap<String, String>, idempotency_key: &str) -> ObservabilityResult<String>;
+    async fn status(&self, run_id: &str) -> ObservabilityResult<WorkflowRunStatus>;
+}
+
+struct TrackedRun {
+    rule_id: String,
+    event_id: String,
+    run: ActionRun,
+}
+
+#[derive(Default)]
+struct RunbookState {
+    runs: Vec<TrackedRun>,
+    // (rule, action) to the last automatic start
+    last_automatic: HashMap<(String, String), DateTime<Utc>>,
+}
+
+impl RunbookState {
+    fn running(&self, rule_id: &str) -> usize {
+        self.runs.iter().filter(|t| t.rule_id == rule_id && t.run.status == ActionStatus::Running).count()
+    }
+}
+
+fn bind_parameters(action: &AlertAction, rule: &AlertRule, event: &AlertEvent) -> Result<HashMap<String, String>, String> {
+    action
+        .parameters
+        .iter()
+        .map(|(name, binding)| {
+            let value = match binding {
+                ParameterBinding::Literal(value) => Some(value.clone()),
+                ParameterBinding::Metadata(key) => event.metadata.get(key).cloned(),
+                ParameterBinding::Dimension(key) => rule
+                    .query
+                    .dimensions
+                    .as_ref()
+                    .and_then(|d| d.get(key))
+                    .or_else(|| event.metadata.get(key))
+                    .cloned(),
+            };
+            value.map(|v| (name.clone(), v)).ok_or_else(|| format!("No value for parameter '{}' ({:?})", name, binding))
+        })
+        .collect()
+}
+
+// Starts the runbook workflows linked to alert rules and tracks their runs on the alert events
+pub struct RunbookExecutor {
+    launcher: Arc<dyn WorkflowLauncher>,
+    state: RwLock<RunbookState>,
+}
+
+impl RunbookExecutor {
+    pub fn new(launcher: Arc<dyn WorkflowLauncher>) -> Self {
+        Self { launcher, state: RwLock::new(RunbookState::default()) }
+    }
+
+    // Call before the event is recorded; fills `event.actions`. Suppressed events only
+    // get their manual actions offered, silences hold back automatic runs.
+    pub async fn on_alert(&self, rule: &AlertRule, event: &mut AlertEvent, now: DateTime<Utc>) {
+        if matches!(event.state, AlertState::Resolved) || rule.alert_actions.workflows.is_empty() {
+            return;
+        }
+        let silenced = matches!(event.state, AlertState::Suppressed);
+        for action in &rule.alert_actions.workflows {
+            let mut run = ActionRun {
+                id: uuid::Uuid::new_v4().to_string(),
+                action_id: action.id.clone(),
+                workflow: action.workflow.clone(),
+                parameters: HashMap::new(),
+                status: ActionStatus::Pending,
+                workflow_run_id: None,
+                detail: None,
+                started_by: None,
+                created_at: now,
+                updated_at: now,
+            };
+            match bind_parameters(action, rule, event) {
+                Ok(parameters) => run.parameters = parameters,
+                Err(e) => {
+                    run.status = ActionStatus::Failed;
+                    run.detail = Some(e);
+                }
+            }
+            if let (ActionStatus::Pending, ExecutionMode::Automatic { cooldown_seconds }) = (&run.status, &action.mode) {
+                self.start_automatic(rule, action, *cooldown_seconds, silenced, &mut run, now).await;
+            }
+            self.state.write().await.runs.push(TrackedRun {
+                rule_id: rule.id.clone(),
+                event_id: event.id.clone(),
+                run: run.clone(),
+            });
+            event.actions.push(run);
+        }
+    }
+
+    async fn start_automatic(
+        &self,
+        rule: &AlertRule,
+        action: &AlertAction,
+        cooldown_seconds: i64,
+        silenced: bool,
+        run: &mut ActionRun,
+        now: DateTime<Utc>,
+    ) {
+        let key = (rule.id.clone(), action.id.clone());
+        let skip = {
+            let state = self.state.read().await;
+            let cooldown_until = state.last_automatic.get(&key).map(|last| *last + chrono::Duration::seconds(cooldown_seconds));
+            if silenced {
+                Some("Alert is silenced".to_string())
+            } else if let Some(until) = cooldown_until.filter(|until| now < *until) {
+                Some(format!("Cooling down until {}", until))
+            } else if state.running(&rule.id) >= rule.alert_actions.max_concurrent_runs {
+                Some(format!("{} runs already in flight", rule.alert_actions.max_concurrent_runs))
+            } else {
+                None
+            }
+        };
+        if let Some(reason) = skip {
+            run.status = ActionStatus::Skipped;
+            run.detail = Some(reason);
+            return;
+        }
+        self.state.write().await.last_automatic.insert(key, now);
+        self.launch(run, now).await;
+    }
+
+    async fn launch(&self, run: &mut ActionRun, now: DateTime<Utc>) {
+        match self.launcher.start(&run.workflow, &run.parameters, &run.id).await {
+            Ok(workflow_run_id) => {
+                info!("Started runbook {} as {} for action {}", run.workflow, workflow_run_id, run.action_id);
+                run.status = ActionStatus::Running;
+                run.workflow_run_id = Some(workflow_run_id);
+            }
+            Err(e) => {
+                warn!("Failed to start runbook {}: {}", run.workflow, e);
+                run.status = ActionStatus::Failed;
+                run.detail = Some(e.to_string());
+            }
+        }
+        run.updated_at = now;
+    }
+
+    // The one-click path: starts a pending action, cooldowns don't apply to people
+    pub async fn run_manual(
+        &self,
+        rule: &AlertRule,
+        event_id: &str,
+        action_id: &str,
+        user: impl Into<String>,
+        now: DateTime<Utc>,
+    ) -> ObservabilityResult<ActionRun> {
+        let mut run = {
+            let state = self.state.read().await;
+            let tracked = state
+                .runs
+                .iter()
+                .find(|t| t.event_id == event_id && t.run.action_id == action_id)
+                .ok_or_else(|| ObservabilityError::NotFound(format!("Action {} on alert event {}", action_id, event_id)))?;
+            if tracked.run.status != ActionStatus::Pending {
+                return Err(ObservabilityError::Conflict(format!("Action {} is {:?}, not pending", action_id, tracked.run.status)));
+            }
+            if state.running(&rule.id) >= rule.alert_actions.max_concurrent_runs {
+                return Err(ObservabilityError::Conflict(format!(
+                    "Rule {} already has {} runbook runs in flight",
+                    rule.id, rule.alert_actions.max_concurrent_runs
+                )));
+            }
+            tracked.run.clone()
+        };
+        run.started_by = Some(user.into());
+        self.launch(&mut run, now).await;
+        self.update(&run).await;
+        Ok(run)
+    }
+
+    async fn update(&self, run: &ActionRun) {
+        if let Some(tracked) = self.state.write().await.runs.iter_mut().find(|t| t.run.id == run.id) {
+            tracked.run = run.clone();
+        }
+    }
+
+    // Refreshes the runs still in flight from the workflow engine
+    pub async fn poll(&self, now: DateTime<Utc>) -> ObservabilityResult<usize> {
+        let running: Vec<ActionRun> = self
+            .state
+            .read()
+            .await
+            .runs
+            .iter()
+            .filter(|t| t.run.status == ActionStatus::Running)
+            .map(|t| t.run.clone())
+            .collect();
+        let mut finished = 0;
+        for mut run in running {
+            let Some(workflow_run_id) = run.workflow_run_id.clone() else { continue };
+            match self.launcher.status(&workflow_run_id).await? {
+                WorkflowRunStatus::Running => continue,
+                WorkflowRunStatus::Succeeded => run.status = ActionStatus::Succeeded,
+                WorkflowRunStatus::Failed(e) => {
+                    run.status = ActionStatus::Failed;
+                    run.detail = Some(e);
+                }
+            }
+            run.updated_at = now;
+            self.update(&run).await;
+            finished += 1;
+        }
+        Ok(finished)
+    }
+
+    pub async fn runs(&self, event_id: &str) -> Vec<ActionRun> {
+        let state = self.state.read().await;
+        state.runs.iter().filter(|t| t.event_id == event_id).map(|t| t.run.clone()).collect()
+    }
+
+    // Recorded events carry their actions as of firing; this brings them up to date
+    pub async fn annotate(&self, events: &mut [AlertEvent]) {
+        for event in events.iter_mut() {
+            let runs = self.runs(&event.id).await;
+            if !runs.is_empty() {
+                event.actions = runs;
+            }
+        }
+    }
+
+    pub fn spawn_worker(self: Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = self.poll(Utc::now()).await {
+                    warn!("Failed to poll runbook runs: {}", e);
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{AggregationType, AlertCondition, AlertSeverity, ComparisonOperator, MetricQuery};
+    use chrono::TimeZone;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Engine {
+        started: Mutex<Vec<(String, HashMap<String, String>)>>,
+        finished: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl WorkflowLauncher for Engine {
+        async fn start(&self, workflow: &str, parameters: &HashMap<String, String>, _key: &str) -> ObservabilityResult<String> {
+            let mut started = self.started.lock().unwrap();
+            started.push((workflow.to_string(), parameters.clone()));
+            Ok(format!("run-{}", started.len()))
+        }
+
+        async fn status(&self, run_id: &str) -> ObservabilityResult<WorkflowRunStatus> {
+            let done = self.finished.lock().unwrap().iter().any(|r| r == run_id);
+            Ok(if done { WorkflowRunStatus::Succeeded } else { WorkflowRunStatus::Running })
+        }
+    }
+
+    fn at(minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, 3, 2, minute, 0).unwrap()
+    }
+
+    fn rule(mode: ExecutionMode) -> AlertRule {
+        AlertRule {
+            id: "db-orders-connections".to_string(),
+            name: "orders connections".to_string(),
+            description: String::new(),
+            severity: AlertSeverity::Critical,
+            query: MetricQuery {
+                metric_name: "connections".to_string(),
+                namespace: "Sirsi/Database".to_string(),
+                dimensions: Some(HashMap::from([("cluster".to_string(), "orders".to_string())])),
+                aggregation: AggregationType::Maximum,
+                period: 60,
+                start_time: at(0),
+                end_time: at(0),
+                include_exemplars: false,
+            },
+            condition: AlertCondition::Threshold {
+                operator: ComparisonOperator::GreaterThan,
+                threshold: 900.0,
+                duration_seconds: 120,
+            },
+            notification_channels: Vec::new(),
+            evaluation_interval: 60,
+            enabled: true,
+            alert_actions: AlertActions {
+                workflows: vec![AlertAction {
+                    id: "recycle-pool".to_string(),
+                    workflow: "db.recycle-connection-pool".to_string(),
+                    parameters: HashMap::from([
+                        ("instance".to_string(), ParameterBinding::Metadata("resource_id".to_string())),
+                        ("cluster".to_string(), ParameterBinding::Dimension("cluster".to_string())),
+                        ("drain".to_string(), ParameterBinding::Literal("true".to_string())),
+                    ]),
+                    mode,
+                }],
+                max_concurrent_runs: 1,
+            },
+        }
+    }
+
+    fn event(state: AlertState, timestamp: DateTime<Utc>) -> AlertEvent {
+        AlertEvent {
+            id: uuid::Uuid::new_v4().to_string(),
+            rule_id: "db-orders-connections".to_string(),
+            severity: AlertSeverity::Critical,
+            state,
+            message: "Connections above 900".to_string(),
+            value: 950.0,
+            timestamp,
+            resolved_at: None,
+            metadata: HashMap::from([("resource_id".to_string(), "db-orders-2".to_string())]),
+            actions: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_automatic_action_binds_parameters_and_cools_down() {
+        let engine = Arc::new(Engine::default());
+        let runbooks = RunbookExecutor::new(engine.clone());
+        let rule = rule(ExecutionMode::Automatic { cooldown_seconds: 600 });
+
+        let mut first = event(AlertState::Firing, at(0));
+        runbooks.on_alert(&rule, &mut first, at(0)).await;
+        assert_eq!(first.actions.len(), 1);
+        assert_eq!(first.actions[0].status, ActionStatus::Running);
+        let started = engine.started.lock().unwrap().clone();
+        assert_eq!(started.len(), 1);
+        assert_eq!(started[0].0, "db.recycle-connection-pool");
+        assert_eq!(started[0].1["instance"], "db-orders-2");
+        assert_eq!(started[0].1["cluster"], "orders");
+        assert_eq!(started[0].1["drain"], "true");
+
+        // It flaps: resolved, then firing again inside the cooldown
+        runbooks.on_alert(&rule, &mut event(AlertState::Resolved, at(2)), at(2)).await;
+        let mut second = event(AlertState::Firing, at(4));
+        runbooks.on_alert(&rule, &mut second, at(4)).await;
+        assert_eq!(second.actions[0].status, ActionStatus::Skipped);
+        assert!(second.actions[0].detail.as_deref().unwrap().contains("Cooling down"));
+        assert_eq!(engine.started.lock().unwrap().len(), 1);
+
+        // Past the cooldown, but the first run is still going
+        let mut third = event(AlertState::Firing, at(11));
+        runbooks.on_alert(&rule, &mut third, at(11)).await;
+        assert_eq!(third.actions[0].status, ActionStatus::Skipped);
+
+        engine.finished.lock().unwrap().push("run-1".to_string());
+        assert_eq!(runbooks.poll(at(12)).await.unwrap(), 1);
+        let mut timeline = vec![first.clone()];
+        runbooks.annotate(&mut timeline).await;
+        assert_eq!(timeline[0].actions[0].status, ActionStatus::Succeeded);
+
+        let mut fourth = event(AlertState::Firing, at(13));
+        runbooks.on_alert(&rule, &mut fourth, at(13)).await;
+        assert_eq!(fourth.actions[0].status, ActionStatus::Running);
+        assert_eq!(engine.started.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_manual_action_waits_and_silences_hold_automatic_runs() {
+        let engine = Arc::new(Engine::default());
+        let runbooks = RunbookExecutor::new(engine.clone());
+        let manual = rule(ExecutionMode::Manual);
+
+        let mut fired = event(AlertState::Firing, at(0));
+        runbooks.on_alert(&manual, &mut fired, at(0)).await;
+        assert_eq!(fired.actions[0].status, ActionStatus::Pending);
+        assert_eq!(fired.actions[0].parameters["instance"], "db-orders-2");
+        assert!(engine.started.lock().unwrap().is_empty());
+
+        let run = runbooks.run_manual(&manual, &fired.id, "recycle-pool", "oncall@sirsi.dev", at(1)).await.unwrap();
+        assert_eq!(run.status, ActionStatus::Running);
+        assert_eq!(run.started_by.as_deref(), Some("oncall@sirsi.dev"));
+        assert_eq!(engine.started.lock().unwrap().len(), 1);
+        assert!(runbooks.run_manual(&manual, &fired.id, "recycle-pool", "oncall@sirsi.dev", at(2)).await.is_err());
+
+        let automatic = AlertRule { id: "other".to_string(), ..rule(ExecutionMode::Automatic { cooldown_seconds: 0 }) };
+        let mut silenced = event(AlertState::Suppressed, at(3));
+        silenced.rule_id = "other".to_string();
+        runbooks.on_alert(&automatic, &mut silenced, at(3)).await;
+        assert_eq!(silenced.actions[0].status, ActionStatus::Skipped);
+        assert_eq!(silenced.actions[0].detail.as_deref(), Some("Alert is silenced"));
+        assert_eq!(engine.started.lock().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,535 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use super::{AlertEvent, AlertManager, AlertRule, AlertSeverity, AlertState, NotificationChannel, RunbookExecutor, WebhookSender};
+use crate::error::{ObservabilityError, ObservabilityResult};
+
+pub const SILENCED_BY_KEY: &str = "silenced_by";
+pub const SILENCE_ID_KEY: &str = "silence_id";
+
+// Criteria within a field are alternatives; every non-empty field has to match
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SilenceMatcher {
+    pub rule_ids: Vec<String>,
+    pub severities: Vec<AlertSeverity>,
+    // Matched against the event's metadata
+    pub labels: HashMap<String, String>,
+}
+
+impl SilenceMatcher {
+    pub fn rule(rule_id: impl Into<String>) -> Self {
+        Self { rule_ids: vec![rule_id.into()], ..Self::default() }
+    }
+
+    pub fn label(key: impl Into<String>, value: impl Into<String>) -> Self {
+        Self { labels: HashMap::from([(key.into(), value.into())]), ..Self::default() }
+    }
+
+    pub fn with_severity(mut self, severity: AlertSeverity) -> Self {
+        self.severities.push(severity);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rule_ids.is_empty() && self.severities.is_empty() && self.labels.is_empty()
+    }
+
+    pub fn matches(&self, event: &AlertEvent) -> bool {
+        (self.rule_ids.is_empty() || self.rule_ids.contains(&event.rule_id))
+            && (self.severities.is_empty() || self.severities.contains(&event.severity))
+            && self.labels.iter().all(|(k, v)| event.metadata.get(k) == Some(v))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Silence {
+    pub id: String,
+    pub matcher: SilenceMatcher,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub comment: String,
+    pub creator: String,
+    pub created_at: DateTime<Utc>,
+    pub reminder_sent: bool,
+}
+
+impl Silence {
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.start <= now && now < self.end
+    }
+
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.end <= now
+    }
+}
+
+pub struct SilenceManager {
+    silences: RwLock<HashMap<String, Silence>>,
+    reminder_lead: Duration,
+}
+
+impl Default for SilenceManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SilenceManager {
+    pub fn new() -> Self {
+        Self {
+            silences: RwLock::new(HashMap::new()),
+            reminder_lead: Duration::from_secs(15 * 60),
+        }
+    }
+
+    // How long before a silence ends its expiry reminder goes out
+    pub fn with_reminder_lead(mut self, lead: Duration) -> Self {
+        self.reminder_lead = lead;
+        self
+    }
+
+    pub async fn create_silence(
+        &self,
+        matcher: SilenceMatcher,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        comment: impl Into<String>,
+        creator: impl Into<String>,
+    ) -> ObservabilityResult<Silence> {
+        // An empty matcher would silence every alert in the system
+        if matcher.is_empty() {
+            return Err(ObservabilityError::Validation("Silence matcher must select something".to_string()));
+        }
+        if end <= start {
+            return Err(ObservabilityError::Validation("Silence must end after it starts".to_string()));
+        }
+        let silence = Silence {
+            id: uuid::Uuid::new_v4().to_string(),
+            matcher,
+            start,
+            end,
+            comment: comment.into(),
+            creator: creator.into(),
+            created_at: Utc::now(),
+            reminder_sent: false,
+        };
+        info!("Created silence {} from {} to {} by {}", silence.id, start, end, silence.creator);
+        self.silences.write().await.insert(silence.id.clone(), silence.clone());
+        Ok(silence)
+    }
+
+    pub async fn get_silence(&self, id: &str) -> ObservabilityResult<Silence> {
+        self.silences
+            .read()
+            .await
+            .get(id)
+            .cloned()
+            .ok_or_else(|| ObservabilityError::NotFound(format!("Silence {}", id)))
+    }
+
+    // Pending and active silences, plus expired ones when asked for
+    pub async fn list_silences(&self, now: DateTime<Utc>, include_expired: bool) -> Vec<Silence> {
+        let mut silences: Vec<Silence> = self
+            .silences
+            .read()
+            .await
+            .values()
+            .filter(|s| include_expired || !s.is_expired(now))
+            .cloned()
+            .collect();
+        silences.sort_by_key(|s| s.start);
+        silences
+    }
+
+    pub async fn delete_silence(&self, id: &str) -> ObservabilityResult<()> {
+        self.silences
+            .write()
+            .await
+            .remove(id)
+            .map(|_| ())
+            .ok_or_else(|| ObservabilityError::NotFound(format!("Silence {}", id)))
+    }
+
+    pub async fn matching(&self, event: &AlertEvent, now: DateTime<Utc>) -> Vec<Silence> {
+        let mut matching: Vec<Silence> = self
+            .silences
+            .read()
+            .await
+            .values()
+            .filter(|s| s.is_active(now) && s.matcher.matches(event))
+            .cloned()
+            .collect();
+        matching.sort_by_key(|s| s.start);
+        matching
+    }
+
+    // Overlapping or back-to-back silences compose, so this is the end of the whole chain
+    pub async fn silenced_until(&self, event: &AlertEvent, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let silences = self.silences.read().await;
+        let candidates: Vec<&Silence> = silences.values().filter(|s| s.matcher.matches(event)).collect();
+        let mut until: Option<DateTime<Utc>> = None;
+        loop {
+            let cursor = until.unwrap_or(now);
+            let extended = candidates
+                .iter()
+                .filter(|s| s.start <= cursor && cursor < s.end)
+                .map(|s| s.end)
+                .max();
+            match extended {
+                Some(end) if until.is_none_or(|u| end > u) => until = Some(end),
+                _ => return until,
+            }
+        }
+    }
+
+    // Reminder events for silences about to end; each silence reminds once
+    pub async fn due_reminders(&self, now: DateTime<Utc>) -> Vec<AlertEvent> {
+        let lead = chrono::Duration::from_std(self.reminder_lead).unwrap_or(chrono::Duration::MAX);
+        let mut silences = self.silences.write().await;
+        silences
+            .values_mut()
+            .filter(|s| !s.reminder_sent && s.is_active(now) && s.end - now <= lead)
+            .map(|silence| {
+                silence.reminder_sent = true;
+                AlertEvent {
+                    id: format!("silence-{}-expiring", silence.id),
+                    rule_id: format!("silence/{}", silence.id),
+                    severity: AlertSeverity::Info,
+                    state: AlertState::Firing,
+                    message: format!("Silence by {} ends at {}: {}", silence.creator, silence.end, silence.comment),
+                    value: (silence.end - now).num_seconds() as f64,
+                    timestamp: now,
+                    resolved_at: None,
+                    metadata: HashMap::from([(SILENCE_ID_KEY.to_string(), silence.id.clone())]),
+                    actions: Vec::new(),
+                }
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+pub trait AlertNotifier: Send + Sync {
+    async fn notify(&self, channel: &NotificationChannel, event: &AlertEvent, now: DateTime<Utc>) -> ObservabilityResult<()>;
+}
+
+#[async_trait]
+impl AlertNotifier for WebhookSender {
+    async fn notify(&self, channel: &NotificationChannel, event: &AlertEvent, now: DateTime<Utc>) -> ObservabilityResult<()> {
+        self.send(channel, event, now).await.map(|_| ())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DispatchOutcome {
+    pub event: AlertEvent,
+    pub silenced_by: Vec<String>,
+    pub notified: usize,
+}
+
+struct HeldAlert {
+    rule: AlertRule,
+    event: AlertEvent,
+}
+
+// Records every alert event, and notifies its rule's channels unless a silence covers it.
+// Firing alerts held back by a silence are notified once it ends if nothing resolved them.
+pub struct AlertDispatcher {
+    alerts: Arc<dyn AlertManager>,
+    silences: Arc<SilenceManager>,
+    notifier: Arc<dyn AlertNotifier>,
+    runbooks: Option<Arc<RunbookExecutor>>,
+    held: RwLock<HashMap<String, HeldAlert>>,
+}
+
+impl AlertDispatcher {
+    pub fn new(alerts: Arc<dyn AlertManager>, silences: Arc<SilenceManager>, notifier: Arc<dyn AlertNotifier>) -> Self {
+        Self { alerts, silences, notifier, runbooks: None, held: RwLock::new(HashMap::new()) }
+    }
+
+    // Starts or offers the rules' runbook actions as their alerts are dispatched
+    pub fn with_runbooks(mut self, runbooks: Arc<RunbookExecutor>) -> Self {
+        self.runbooks = Some(runbooks);
+        self
+    }
+
+    pub async fn dispatch(&self, rule: &AlertRule, mut event: AlertEvent, now: DateTime<Utc>) -> ObservabilityResult<DispatchOutcome> {
+        let silenced_by: Vec<String> = self.silences.matching(&event, now).await.into_iter().map(|s| s.id).collect();
+        let was_held = self.held.write().await.remove(&event.rule_id).is_some();
+
+        if !silenced_by.is_empty() {
+            if matches!(event.state, AlertState::Firing) {
+                self.held.write().await.insert(
+                    event.rule_id.clone(),
+                    HeldAlert { rule: rule.clone(), event: event.clone() },
+                );
+            }
+            event.state = AlertState::Suppressed;
+            event.metadata.insert(SILENCED_BY_KEY.to_string(), silenced_by.join(","));
+            if let Some(runbooks) = &self.runbooks {
+                runbooks.on_alert(rule, &mut event, now).await;
+            }
+            let event = self.alerts.record_alert_event(event).await?;
+            return Ok(DispatchOutcome { event, silenced_by, notified: 0 });
+        }
+
+        if let Some(runbooks) = &self.runbooks {
+            runbooks.on_alert(rule, &mut event, now).await;
+        }
+        let event = self.alerts.record_alert_event(event).await?;
+        // Nobody was told it fired, so there's nothing to resolve
+        if was_held && matches!(event.state, AlertState::Resolved) {
+            return Ok(DispatchOutcome { event, silenced_by, notified: 0 });
+        }
+        let notified = self.notify(rule, &event, now).await;
+        Ok(DispatchOutcome { event, silenced_by, notified })
+    }
+
+    async fn notify(&self, rule: &AlertRule, event: &AlertEvent, now: DateTime<Utc>) -> usize {
+        let mut notified = 0;
+        for channel in rule.notification_channels.iter().filter(|c| c.enabled) {
+            match self.notifier.notify(channel, event, now).await {
+                Ok(()) => notified += 1,
+                Err(e) => warn!("Failed to notify {} about alert {}: {}", channel.id, event.id, e),
+            }
+        }
+        notified
+    }
+
+    // Notifies held alerts whose silences have all ended; a new dispatch for the rule
+    // (e.g. it resolved) replaces or clears the held event
+    pub async fn release_expired(&self, now: DateTime<Utc>) -> ObservabilityResult<Vec<DispatchOutcome>> {
+        let held: Vec<(String, HeldAlert)> = self.held.write().await.drain().collect();
+        let mut released = Vec::new();
+        for (rule_id, alert) in held {
+            if !self.silences.matching(&alert.event, now).await.is_empty() {
+                self.held.write().await.insert(rule_id, alert);
+                continue;
+            }
+            let mut event = alert.event;
+            event.id = uuid::Uuid::new_v4().to_string();
+            event.timestamp = now;
+            event.metadata.insert("silence_ended".to_string(), "true".to_string());
+            released.push(self.dispatch(&alert.rule, event, now).await?);
+        }
+        Ok(released)
+    }
+
+    pub async fn send_reminders(&self, now: DateTime<Utc>) -> ObservabilityResult<Vec<AlertEvent>> {
+        let mut recorded = Vec::new();
+        for reminder in self.silences.due_reminders(now).await {
+            recorded.push(self.alerts.record_alert_event(reminder).await?);
+        }
+        Ok(recorded)
+    }
+
+    pub fn spawn_worker(self: Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let now = Utc::now();
+                if let Err(e) = self.release_expired(now).await {
+                    warn!("Failed to release silenced alerts: {}", e);
+                }
+                if let Err(e) = self.send_reminders(now).await {
+                    warn!("Failed to send silence reminders: {}", e);
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{AggregationType, AlertActions, AlertCondition, ComparisonOperator, MetricQuery, NotificationType, WebhookDelivery};
+    use chrono::TimeZone;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct EventLog {
+        events: Mutex<Vec<AlertEvent>>,
+    }
+
+    #[async_trait]
+    impl AlertManager for EventLog {
+        async fn create_alert_rule(&self, rule: AlertRule) -> ObservabilityResult<AlertRule> {
+            Ok(rule)
+        }
+
+        async fn update_alert_rule(&self, rule: AlertRule) -> ObservabilityResult<AlertRule> {
+            Ok(rule)
+        }
+
+        async fn delete_alert_rule(&self, _id: &str) -> ObservabilityResult<()> {
+            Ok(())
+        }
+
+        async fn get_alert_rule(&self, id: &str) -> ObservabilityResult<AlertRule> {
+            Err(ObservabilityError::NotFound(id.to_string()))
+        }
+
+        async fn list_alert_rules(&self) -> ObservabilityResult<Vec<AlertRule>> {
+            Ok(Vec::new())
+        }
+
+        async fn get_alert_events(&self, _rule_id: Option<String>) -> ObservabilityResult<Vec<AlertEvent>> {
+            Ok(self.events.lock().unwrap().clone())
+        }
+
+        async fn record_alert_event(&self, event: AlertEvent) -> ObservabilityResult<AlertEvent> {
+            self.events.lock().unwrap().push(event.clone());
+            Ok(event)
+        }
+
+        async fn list_webhook_deliveries(&self, _channel_id: &str) -> ObservabilityResult<Vec<WebhookDelivery>> {
+            Ok(Vec::new())
+        }
+
+        async fn redeliver(&self, delivery_id: &str) -> ObservabilityResult<WebhookDelivery> {
+            Err(ObservabilityError::NotFound(delivery_id.to_string()))
+        }
+    }
+
+    #[derive(Default)]
+    struct Pager {
+        sent: Mutex<Vec<(String, String)>>,
+    }
+
+    #[async_trait]
+    impl AlertNotifier for Pager {
+        async fn notify(&self, channel: &NotificationChannel, event: &AlertEvent, _now: DateTime<Utc>) -> ObservabilityResult<()> {
+            self.sent.lock().unwrap().push((channel.id.clone(), event.rule_id.clone()));
+            Ok(())
+        }
+    }
+
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, 3, hour, minute, 0).unwrap()
+    }
+
+    fn rule() -> AlertRule {
+        AlertRule {
+            id: "db-orders-cpu".to_string(),
+            name: "orders CPU".to_string(),
+            description: String::new(),
+            severity: AlertSeverity::Critical,
+            query: MetricQuery {
+                metric_name: "cpu_utilization".to_string(),
+                namespace: "Sirsi/Database".to_string(),
+                dimensions: None,
+                aggregation: AggregationType::Average,
+                period: 60,
+                start_time: at(0, 0),
+                end_time: at(0, 0),
+                include_exemplars: false,
+            },
+            condition: AlertCondition::Threshold {
+                operator: ComparisonOperator::GreaterThan,
+                threshold: 90.0,
+                duration_seconds: 300,
+            },
+            notification_channels: vec![NotificationChannel {
+                id: "oncall".to_string(),
+                name: "On-call".to_string(),
+                channel_type: NotificationType::PagerDuty,
+                settings: HashMap::new(),
+                enabled: true,
+            }],
+            evaluation_interval: 60,
+            enabled: true,
+            alert_actions: AlertActions::default(),
+        }
+    }
+
+    fn event(state: AlertState, timestamp: DateTime<Utc>) -> AlertEvent {
+        AlertEvent {
+            id: uuid::Uuid::new_v4().to_string(),
+            rule_id: "db-orders-cpu".to_string(),
+            severity: AlertSeverity::Critical,
+            state,
+            message: "CPU above 90%".to_string(),
+            value: 97.0,
+            timestamp,
+            resolved_at: None,
+            metadata: HashMap::from([("instance_id".to_string(), "db-orders".to_string())]),
+            actions: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_alert_during_silence_notifies_after_it_ends() {
+        let log = Arc::new(EventLog::default());
+        let pager = Arc::new(Pager::default());
+        let silences = Arc::new(SilenceManager::new());
+        let dispatcher = AlertDispatcher::new(log.clone(), silences.clone(), pager.clone());
+
+        // Two overlapping silences, one by label and one by rule, cover 02:00-04:00 together
+        silences
+            .create_silence(SilenceMatcher::label("instance_id", "db-orders"), at(2, 0), at(3, 0), "patching", "ops")
+            .await
+            .unwrap();
+        let by_rule = silences
+            .create_silence(SilenceMatcher::rule("db-orders-cpu"), at(2, 30), at(4, 0), "reindex", "dba")
+            .await
+            .unwrap();
+        assert_eq!(silences.silenced_until(&event(AlertState::Firing, at(2, 0)), at(2, 0)).await, Some(at(4, 0)));
+
+        let outcome = dispatcher.dispatch(&rule(), event(AlertState::Firing, at(2, 45)), at(2, 45)).await.unwrap();
+        assert_eq!(outcome.silenced_by.len(), 2);
+        assert!(matches!(outcome.event.state, AlertState::Suppressed));
+        assert!(pager.sent.lock().unwrap().is_empty());
+        assert_eq!(log.events.lock().unwrap().len(), 1);
+
+        // The label silence is over but the rule silence still covers it
+        assert!(dispatcher.release_expired(at(3, 30)).await.unwrap().is_empty());
+        let reminders = dispatcher.send_reminders(at(3, 50)).await.unwrap();
+        assert_eq!(reminders.len(), 1);
+        assert_eq!(reminders[0].metadata[SILENCE_ID_KEY], by_rule.id);
+        assert!(dispatcher.send_reminders(at(3, 55)).await.unwrap().is_empty());
+
+        let released = dispatcher.release_expired(at(4, 0)).await.unwrap();
+        assert_eq!(released.len(), 1);
+        assert!(matches!(released[0].event.state, AlertState::Firing));
+        assert_eq!(released[0].notified, 1);
+        assert_eq!(pager.sent.lock().unwrap().len(), 1);
+        assert!(dispatcher.release_expired(at(4, 5)).await.unwrap().is_empty());
+
+        assert_eq!(silences.list_silences(at(4, 0), false).await.len(), 0);
+        assert_eq!(silences.list_silences(at(4, 0), true).await.len(), 2);
+        silences.delete_silence(&by_rule.id).await.unwrap();
+        assert!(silences.get_silence(&by_rule.id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_alert_resolved_during_silence_stays_quiet() {
+        let pager = Arc::new(Pager::default());
+        let silences = Arc::new(SilenceManager::new());
+        let dispatcher = AlertDispatcher::new(Arc::new(EventLog::default()), silences.clone(), pager.clone());
+        let matcher = SilenceMatcher::label("instance_id", "db-orders").with_severity(AlertSeverity::Critical);
+        silences.create_silence(matcher, at(2, 0), at(3, 0), "failover test", "ops").await.unwrap();
+        assert!(silences
+            .create_silence(SilenceMatcher::default(), at(2, 0), at(3, 0), "everything", "ops")
+            .await
+            .is_err());
+
+        dispatcher.dispatch(&rule(), event(AlertState::Firing, at(2, 10)), at(2, 10)).await.unwrap();
+        dispatcher.dispatch(&rule(), event(AlertState::Resolved, at(2, 20)), at(2, 20)).await.unwrap();
+        assert!(dispatcher.release_expired(at(3, 0)).await.unwrap().is_empty());
+
+        let mut warning = event(AlertState::Firing, at(2, 30));
+        warning.severity = AlertSeverity::Warning;
+        let outcome = dispatcher.dispatch(&rule(), warning, at(2, 30)).await.unwrap();
+        assert!(outcome.silenced_by.is_empty());
+        assert_eq!(pager.sent.lock().unwrap().len(), 1);
+    }
+}
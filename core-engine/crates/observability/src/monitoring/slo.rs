@@ -0,0 +1,775 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+use super::{
+    AggregationType, AlertActions, AlertCondition, AlertManager, AlertRule, AlertSeverity, ComparisonOperator, MetricDataPoint,
+    MetricQuery, MetricValue, MetricsManager, NotificationChannel,
+};
+use crate::error::{ObservabilityError, ObservabilityResult};
+
+pub const SLO_NAMESPACE: &str = "Sirsi/SLO";
+pub const ERROR_RATE_METRIC: &str = "slo_error_rate";
+pub const BUDGET_REMAINING_METRIC: &str = "slo_error_budget_remaining";
+pub const DIM_SLO_ID: &str = "slo_id";
+pub const DIM_WINDOW: &str = "window";
+
+const DEFAULT_HISTORY_LIMIT: usize = 1440;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SloIndicator {
+    // SLI = good / total, both counted as the sum of the series' values
+    Ratio { good: MetricQuery, total: MetricQuery },
+    // SLI = share of latency samples at or under the threshold
+    Latency { query: MetricQuery, threshold_ms: f64 },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BurnRateAlert {
+    pub window_seconds: i64,
+    // Multiple of the sustainable error rate that fires the alert
+    pub burn_rate: f64,
+    pub severity: AlertSeverity,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BurnRatePolicy {
+    pub fast: BurnRateAlert,
+    pub slow: BurnRateAlert,
+}
+
+impl Default for BurnRatePolicy {
+    // 14.4x over 1h spends 2% of a 30 day budget; 6x over 6h spends 5%
+    fn default() -> Self {
+        Self {
+            fast: BurnRateAlert { window_seconds: 3600, burn_rate: 14.4, severity: AlertSeverity::Critical },
+            slow: BurnRateAlert { window_seconds: 6 * 3600, burn_rate: 6.0, severity: AlertSeverity::Warning },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SloDefinition {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    // Percentage of good events, e.g. 99.9
+    pub target: f64,
+    pub window_seconds: i64,
+    pub indicator: SloIndicator,
+    pub alerting: BurnRatePolicy,
+    pub notification_channels: Vec<NotificationChannel>,
+    pub evaluation_interval: i32,
+}
+
+impl SloDefinition {
+    pub fn new(id: impl Into<String>, name: impl Into<String>, target: f64, window_seconds: i64, indicator: SloIndicator) -> Self {
+        Self {
+            id: id.into(),
+            name: name.into(),
+            description: String::new(),
+            target,
+            window_seconds,
+            indicator,
+            alerting: BurnRatePolicy::default(),
+            notification_channels: Vec::new(),
+            evaluation_interval: 60,
+        }
+    }
+
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = description.into();
+        self
+    }
+
+    pub fn with_alerting(mut self, alerting: BurnRatePolicy) -> Self {
+        self.alerting = alerting;
+        self
+    }
+
+    pub fn with_notification_channels(mut self, channels: Vec<NotificationChannel>) -> Self {
+        self.notification_channels = channels;
+        self
+    }
+
+    pub fn validate(&self) -> ObservabilityResult<()> {
+        let invalid = |msg: &str| Err(ObservabilityError::Validation(format!("SLO {}: {}", self.id, msg)));
+        if self.id.is_empty() {
+            return Err(ObservabilityError::Validation("SLO id is required".to_string()));
+        }
+        if !(self.target > 0.0 && self.target < 100.0) {
+            return invalid("target must be between 0 and 100 exclusive");
+        }
+        if self.window_seconds <= 0 || self.evaluation_interval <= 0 {
+            return invalid("window and evaluation interval must be positive");
+        }
+        for alert in [&self.alerting.fast, &self.alerting.slow] {
+            if alert.window_seconds <= 0 || alert.burn_rate <= 0.0 {
+                return invalid("burn-rate windows and rates must be positive");
+            }
+        }
+        if let SloIndicator::Latency { threshold_ms, .. } = &self.indicator {
+            if *threshold_ms <= 0.0 {
+                return invalid("latency threshold must be positive");
+            }
+        }
+        Ok(())
+    }
+
+    // Fraction of events allowed to be bad
+    pub fn error_budget(&self) -> f64 {
+        1.0 - self.target / 100.0
+    }
+
+    pub fn fast_burn_rule_id(&self) -> String {
+        format!("{}-fast-burn", self.id)
+    }
+
+    pub fn slow_burn_rule_id(&self) -> String {
+        format!("{}-slow-burn", self.id)
+    }
+
+    fn lookback_seconds(&self) -> i64 {
+        self.window_seconds.max(self.alerting.fast.window_seconds).max(self.alerting.slow.window_seconds)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct EventSample {
+    pub timestamp: DateTime<Utc>,
+    pub good: f64,
+    pub total: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BurnRate {
+    pub window_seconds: i64,
+    pub error_rate: f64,
+    pub burn_rate: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SloStatus {
+    pub slo_id: String,
+    pub evaluated_at: DateTime<Utc>,
+    pub target: f64,
+    // None until the window holds any events
+    pub sli: Option<f64>,
+    pub good_events: f64,
+    pub total_events: f64,
+    // 1.0 is untouched, 0.0 is exhausted; negative once the SLO is breached
+    pub error_budget_remaining: f64,
+    pub fast_burn: BurnRate,
+    pub slow_burn: BurnRate,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SloStatusReport {
+    pub definition: SloDefinition,
+    pub current: Option<SloStatus>,
+    // Oldest first
+    pub history: Vec<SloStatus>,
+}
+
+fn value_sum(value: &MetricValue) -> f64 {
+    match value {
+        MetricValue::Single(v) => *v,
+        MetricValue::Multiple(values) => values.iter().sum(),
+        MetricValue::Distribution { sum, .. } => *sum,
+    }
+}
+
+fn latency_counts(value: &MetricValue, threshold_ms: f64) -> (f64, f64) {
+    match value {
+        MetricValue::Single(v) => (if *v <= threshold_ms { 1.0 } else { 0.0 }, 1.0),
+        MetricValue::Multiple(values) => (
+            values.iter().filter(|v| **v <= threshold_ms).count() as f64,
+            values.len() as f64,
+        ),
+        // A distribution straddling the threshold can't be split, so it only counts as
+        // good when even its slowest sample made it
+        MetricValue::Distribution { count, max, .. } => {
+            let count = *count as f64;
+            (if *max <= threshold_ms { count } else { 0.0 }, count)
+        }
+    }
+}
+
+fn ratio_samples(good: &[MetricDataPoint], total: &[MetricDataPoint]) -> Vec<EventSample> {
+    let good = good.iter().map(|p| EventSample { timestamp: p.timestamp, good: value_sum(&p.value), total: 0.0 });
+    let total = total.iter().map(|p| EventSample { timestamp: p.timestamp, good: 0.0, total: value_sum(&p.value) });
+    good.chain(total).collect()
+}
+
+fn latency_samples(points: &[MetricDataPoint], threshold_ms: f64) -> Vec<EventSample> {
+    points
+        .iter()
+        .map(|p| {
+            let (good, total) = latency_counts(&p.value, threshold_ms);
+            EventSample { timestamp: p.timestamp, good, total }
+        })
+        .collect()
+}
+
+// Rolling windows cover (now - window, now]: a sample exactly one window old has aged out
+fn window_counts(samples: &[EventSample], window_seconds: i64, now: DateTime<Utc>) -> (f64, f64) {
+    let start = now - chrono::Duration::seconds(window_seconds);
+    samples
+        .iter()
+        .filter(|s| s.timestamp > start && s.timestamp <= now)
+        .fold((0.0, 0.0), |(good, total), s| (good + s.good, total + s.total))
+}
+
+fn error_rate(good: f64, total: f64) -> f64 {
+    if total <= 0.0 {
+        0.0
+    } else {
+        ((total - good) / total).clamp(0.0, 1.0)
+    }
+}
+
+fn burn_rate(definition: &SloDefinition, samples: &[EventSample], window_seconds: i64, now: DateTime<Utc>) -> BurnRate {
+    let (good, total) = window_counts(samples, window_seconds, now);
+    let error_rate = error_rate(good, total);
+    BurnRate { window_seconds, error_rate, burn_rate: error_rate / definition.error_budget() }
+}
+
+pub fn compute_status(definition: &SloDefinition, samples: &[EventSample], now: DateTime<Utc>) -> SloStatus {
+    let (good, total) = window_counts(samples, definition.window_seconds, now);
+    let good = good.min(total);
+    let (sli, error_budget_remaining) = if total > 0.0 {
+        let allowed_bad = total * definition.error_budget();
+        (Some(good / total * 100.0), 1.0 - (total - good) / allowed_bad)
+    } else {
+        (None, 1.0)
+    };
+    SloStatus {
+        slo_id: definition.id.clone(),
+        evaluated_at: now,
+        target: definition.target,
+        sli,
+        good_events: good,
+        total_events: total,
+        error_budget_remaining,
+        fast_burn: burn_rate(definition, samples, definition.alerting.fast.window_seconds, now),
+        slow_burn: burn_rate(definition, samples, definition.alerting.slow.window_seconds, now),
+    }
+}
+
+fn window_label(seconds: i64) -> String {
+    if seconds % 3600 == 0 {
+        format!("{}h", seconds / 3600)
+    } else if seconds % 60 == 0 {
+        format!("{}m", seconds / 60)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+fn burn_rate_rule(definition: &SloDefinition, id: String, kind: &str, alert: &BurnRateAlert) -> AlertRule {
+    let end_time = Utc::now();
+    let window = window_label(alert.window_seconds);
+    AlertRule {
+        id,
+        name: format!("{} {} burn", definition.name, kind),
+        description: format!(
+            "Error budget for SLO {} is burning at {}x or more over {}",
+            definition.name, alert.burn_rate, window
+        ),
+        severity: alert.severity.clone(),
+        query: MetricQuery {
+            metric_name: ERROR_RATE_METRIC.to_string(),
+            namespace: SLO_NAMESPACE.to_string(),
+            dimensions: Some(HashMap::from([
+                (DIM_SLO_ID.to_string(), definition.id.clone()),
+                (DIM_WINDOW.to_string(), window),
+            ])),
+            aggregation: AggregationType::Maximum,
+            period: definition.evaluation_interval,
+            start_time: end_time - chrono::Duration::seconds(alert.window_seconds),
+            end_time,
+            include_exemplars: false,
+        },
+        condition: AlertCondition::Threshold {
+            operator: ComparisonOperator::GreaterThanOrEqual,
+            threshold: alert.burn_rate * definition.error_budget(),
+            // Requiring a twelfth of the window filters out blips without delaying pages much
+            duration_seconds: (alert.window_seconds / 12) as i32,
+        },
+        notification_channels: definition.notification_channels.clone(),
+        evaluation_interval: definition.evaluation_interval,
+        enabled: true,
+        alert_actions: AlertActions::default(),
+    }
+}
+
+// Paired fast- and slow-burn rules over the published error-rate series
+pub fn burn_rate_alert_rules(definition: &SloDefinition) -> [AlertRule; 2] {
+    [
+        burn_rate_rule(definition, definition.fast_burn_rule_id(), "fast", &definition.alerting.fast),
+        burn_rate_rule(definition, definition.slow_burn_rule_id(), "slow", &definition.alerting.slow),
+    ]
+}
+
+#[async_trait]
+pub trait SloStore: Send + Sync {
+    async fn save_slo(&self, definition: SloDefinition) -> ObservabilityResult<()>;
+    async fn get_slo(&self, id: &str) -> ObservabilityResult<Option<SloDefinition>>;
+    async fn list_slos(&self) -> ObservabilityResult<Vec<SloDefinition>>;
+    async fn delete_slo(&self, id: &str) -> ObservabilityResult<()>;
+    async fn record_status(&self, status: SloStatus) -> ObservabilityResult<()>;
+    async fn list_statuses(&self, slo_id: &str) -> ObservabilityResult<Vec<SloStatus>>;
+}
+
+pub struct InMemorySloStore {
+    slos: RwLock<HashMap<String, SloDefinition>>,
+    statuses: RwLock<HashMap<String, VecDeque<SloStatus>>>,
+    history_limit: usize,
+}
+
+impl Default for InMemorySloStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InMemorySloStore {
+    pub fn new() -> Self {
+        Self {
+            slos: RwLock::new(HashMap::new()),
+            statuses: RwLock::new(HashMap::new()),
+            history_limit: DEFAULT_HISTORY_LIMIT,
+        }
+    }
+
+    pub fn with_history_limit(mut self, history_limit: usize) -> Self {
+        self.history_limit = history_limit.max(1);
+        self
+    }
+}
+
+#[async_trait]
+impl SloStore for InMemorySloStore {
+    async fn save_slo(&self, definition: SloDefinition) -> ObservabilityResult<()> {
+        self.slos.write().await.insert(definition.id.clone(), definition);
+        Ok(())
+    }
+
+    async fn get_slo(&self, id: &str) -> ObservabilityResult<Option<SloDefinition>> {
+        Ok(self.slos.read().await.get(id).cloned())
+    }
+
+    async fn list_slos(&self) -> ObservabilityResult<Vec<SloDefinition>> {
+        let mut slos: Vec<_> = self.slos.read().await.values().cloned().collect();
+        slos.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(slos)
+    }
+
+    async fn delete_slo(&self, id: &str) -> ObservabilityResult<()> {
+        self.slos.write().await.remove(id);
+        self.statuses.write().await.remove(id);
+        Ok(())
+    }
+
+    async fn record_status(&self, status: SloStatus) -> ObservabilityResult<()> {
+        let mut statuses = self.statuses.write().await;
+        let history = statuses.entry(status.slo_id.clone()).or_default();
+        history.push_back(status);
+        while history.len() > self.history_limit {
+            history.pop_front();
+        }
+        Ok(())
+    }
+
+    async fn list_statuses(&self, slo_id: &str) -> ObservabilityResult<Vec<SloStatus>> {
+        Ok(self.statuses.read().await.get(slo_id).map(|h| h.iter().cloned().collect()).unwrap_or_default())
+    }
+}
+
+pub struct SloManager {
+    metrics: Arc<dyn MetricsManager>,
+    alerts: Arc<dyn AlertManager>,
+    store: Arc<dyn SloStore>,
+}
+
+impl SloManager {
+    pub fn new(metrics: Arc<dyn MetricsManager>, alerts: Arc<dyn AlertManager>) -> Self {
+        Self { metrics, alerts, store: Arc::new(InMemorySloStore::new()) }
+    }
+
+    pub fn with_store(mut self, store: Arc<dyn SloStore>) -> Self {
+        self.store = store;
+        self
+    }
+
+    pub async fn create_slo(&self, definition: SloDefinition) -> ObservabilityResult<SloDefinition> {
+        definition.validate()?;
+        if self.store.get_slo(&definition.id).await?.is_some() {
+            return Err(ObservabilityError::Conflict(format!("SLO {} already exists", definition.id)));
+        }
+        for rule in burn_rate_alert_rules(&definition) {
+            self.alerts.create_alert_rule(rule).await?;
+        }
+        self.store.save_slo(definition.clone()).await?;
+        Ok(definition)
+    }
+
+    // Regenerates both burn-rate rules so thresholds and channels follow the edit
+    pub async fn update_slo(&self, definition: SloDefinition) -> ObservabilityResult<SloDefinition> {
+        definition.validate()?;
+        self.get_slo(&definition.id).await?;
+        for rule in burn_rate_alert_rules(&definition) {
+            match self.alerts.update_alert_rule(rule.clone()).await {
+                Ok(_) => {}
+                // Recreate rules someone deleted by hand
+                Err(ObservabilityError::NotFound(_)) => {
+                    self.alerts.create_alert_rule(rule).await?;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        self.store.save_slo(definition.clone()).await?;
+        Ok(definition)
+    }
+
+    pub async fn delete_slo(&self, id: &str) -> ObservabilityResult<()> {
+        let definition = self.get_slo(id).await?;
+        for rule_id in [definition.fast_burn_rule_id(), definition.slow_burn_rule_id()] {
+            match self.alerts.delete_alert_rule(&rule_id).await {
+                Ok(()) | Err(ObservabilityError::NotFound(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        self.store.delete_slo(id).await
+    }
+
+    pub async fn get_slo(&self, id: &str) -> ObservabilityResult<SloDefinition> {
+        self.store
+            .get_slo(id)
+            .await?
+            .ok_or_else(|| ObservabilityError::NotFound(format!("SLO {}", id)))
+    }
+
+    pub async fn list_slos(&self) -> ObservabilityResult<Vec<SloDefinition>> {
+        self.store.list_slos().await
+    }
+
+    async fn fetch(&self, query: &MetricQuery, start_time: DateTime<Utc>, now: DateTime<Utc>) -> ObservabilityResult<Vec<MetricDataPoint>> {
+        let mut query = query.clone();
+        query.start_time = start_time;
+        query.end_time = now;
+        self.metrics.get_metric_data(query).await
+    }
+
+    async fn samples(&self, definition: &SloDefinition, now: DateTime<Utc>) -> ObservabilityResult<Vec<EventSample>> {
+        let start_time = now - chrono::Duration::seconds(definition.lookback_seconds());
+        match &definition.indicator {
+            SloIndicator::Ratio { good, total } => {
+                let good = self.fetch(good, start_time, now).await?;
+                let total = self.fetch(total, start_time, now).await?;
+                Ok(ratio_samples(&good, &total))
+            }
+            SloIndicator::Latency { query, threshold_ms } => {
+                let points = self.fetch(query, start_time, now).await?;
+                Ok(latency_samples(&points, *threshold_ms))
+            }
+        }
+    }
+
+    async fn publish(&self, status: &SloStatus) -> ObservabilityResult<()> {
+        let point = |name: &str, window: Option<i64>, value: f64| {
+            let mut dimensions = HashMap::from([(DIM_SLO_ID.to_string(), status.slo_id.clone())]);
+            if let Some(window) = window {
+                dimensions.insert(DIM_WINDOW.to_string(), window_label(window));
+            }
+            MetricDataPoint {
+                name: name.to_string(),
+                namespace: SLO_NAMESPACE.to_string(),
+                dimensions,
+                timestamp: status.evaluated_at,
+                value: MetricValue::Single(value),
+                exemplars: Vec::new(),
+            }
+        };
+        self.metrics
+            .put_metric_data(vec![
+                point(ERROR_RATE_METRIC, Some(status.fast_burn.window_seconds), status.fast_burn.error_rate),
+                point(ERROR_RATE_METRIC, Some(status.slow_burn.window_seconds), status.slow_burn.error_rate),
+                point(BUDGET_REMAINING_METRIC, None, status.error_budget_remaining),
+            ])
+            .await
+    }
+
+    pub async fn evaluate(&self, id: &str, now: DateTime<Utc>) -> ObservabilityResult<SloStatus> {
+        let definition = self.get_slo(id).await?;
+        let samples = self.samples(&definition, now).await?;
+        let status = compute_status(&definition, &samples, now);
+        // The status is still worth keeping when the alert series can't be written
+        if let Err(e) = self.publish(&status).await {
+            warn!("Failed to publish burn rates for SLO {}: {}", id, e);
+        }
+        self.store.record_status(status.clone()).await?;
+        Ok(status)
+    }
+
+    pub async fn evaluate_all(&self, now: DateTime<Utc>) -> ObservabilityResult<Vec<SloStatus>> {
+        let mut statuses = Vec::new();
+        for definition in self.store.list_slos().await? {
+            match self.evaluate(&definition.id, now).await {
+                Ok(status) => statuses.push(status),
+                Err(e) => warn!("Failed to evaluate SLO {}: {}", definition.id, e),
+            }
+        }
+        Ok(statuses)
+    }
+
+    pub async fn get_slo_status(&self, id: &str) -> ObservabilityResult<SloStatusReport> {
+        let definition = self.get_slo(id).await?;
+        let history = self.store.list_statuses(id).await?;
+        Ok(SloStatusReport { definition, current: history.last().cloned(), history })
+    }
+
+    pub fn spawn_evaluator(self: Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = self.evaluate_all(Utc::now()).await {
+                    warn!("SLO evaluation pass failed: {}", e);
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{AlertEvent, MetricDefinition, WebhookDelivery};
+    use chrono::TimeZone;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct SeriesMetrics {
+        points: Mutex<Vec<MetricDataPoint>>,
+    }
+
+    #[async_trait]
+    impl MetricsManager for SeriesMetrics {
+        async fn register_metric(&self, _definition: MetricDefinition) -> ObservabilityResult<()> {
+            Ok(())
+        }
+
+        async fn put_metric_data(&self, data_points: Vec<MetricDataPoint>) -> ObservabilityResult<()> {
+            self.points.lock().unwrap().extend(data_points);
+            Ok(())
+        }
+
+        async fn get_metric_data(&self, query: MetricQuery) -> ObservabilityResult<Vec<MetricDataPoint>> {
+            Ok(self
+                .points
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|p| p.name == query.metric_name && p.namespace == query.namespace)
+                .filter(|p| p.timestamp >= query.start_time && p.timestamp <= query.end_time)
+                .cloned()
+                .collect())
+        }
+
+        async fn list_metrics(&self, _namespace: Option<String>) -> ObservabilityResult<Vec<MetricDefinition>> {
+            Ok(Vec::new())
+        }
+
+        async fn delete_metric(&self, _name: &str, _namespace: &str) -> ObservabilityResult<()> {
+            Ok(())
+        }
+    }
+
+    #[derive(Default)]
+    struct RuleStore {
+        rules: Mutex<HashMap<String, AlertRule>>,
+    }
+
+    #[async_trait]
+    impl AlertManager for RuleStore {
+        async fn create_alert_rule(&self, rule: AlertRule) -> ObservabilityResult<AlertRule> {
+            self.rules.lock().unwrap().insert(rule.id.clone(), rule.clone());
+            Ok(rule)
+        }
+
+        async fn update_alert_rule(&self, rule: AlertRule) -> ObservabilityResult<AlertRule> {
+            let mut rules = self.rules.lock().unwrap();
+            if !rules.contains_key(&rule.id) {
+                return Err(ObservabilityError::NotFound(rule.id));
+            }
+            rules.insert(rule.id.clone(), rule.clone());
+            Ok(rule)
+        }
+
+        async fn delete_alert_rule(&self, id: &str) -> ObservabilityResult<()> {
+            self.rules.lock().unwrap().remove(id);
+            Ok(())
+        }
+
+        async fn get_alert_rule(&self, id: &str) -> ObservabilityResult<AlertRule> {
+            self.rules
+                .lock()
+                .unwrap()
+                .get(id)
+                .cloned()
+                .ok_or_else(|| ObservabilityError::NotFound(id.to_string()))
+        }
+
+        async fn list_alert_rules(&self) -> ObservabilityResult<Vec<AlertRule>> {
+            Ok(self.rules.lock().unwrap().values().cloned().collect())
+        }
+
+        async fn get_alert_events(&self, _rule_id: Option<String>) -> ObservabilityResult<Vec<AlertEvent>> {
+            Ok(Vec::new())
+        }
+
+        async fn record_alert_event(&self, event: AlertEvent) -> ObservabilityResult<AlertEvent> {
+            Ok(event)
+        }
+
+        async fn list_webhook_deliveries(&self, _channel_id: &str) -> ObservabilityResult<Vec<WebhookDelivery>> {
+            Ok(Vec::new())
+        }
+
+        async fn redeliver(&self, delivery_id: &str) -> ObservabilityResult<WebhookDelivery> {
+            Err(ObservabilityError::NotFound(delivery_id.to_string()))
+        }
+    }
+
+    fn query(name: &str) -> MetricQuery {
+        MetricQuery {
+            metric_name: name.to_string(),
+            namespace: "Sirsi/API".to_string(),
+            dimensions: None,
+            aggregation: AggregationType::Sum,
+            period: 60,
+            start_time: Utc.timestamp_opt(0, 0).unwrap(),
+            end_time: Utc.timestamp_opt(0, 0).unwrap(),
+            include_exemplars: false,
+        }
+    }
+
+    fn point(name: &str, timestamp: DateTime<Utc>, value: f64) -> MetricDataPoint {
+        MetricDataPoint {
+            name: name.to_string(),
+            namespace: "Sirsi/API".to_string(),
+            dimensions: HashMap::new(),
+            timestamp,
+            value: MetricValue::Single(value),
+            exemplars: Vec::new(),
+        }
+    }
+
+    fn availability() -> SloDefinition {
+        // A one day window keeps the synthetic series small
+        SloDefinition::new(
+            "api-availability",
+            "API availability",
+            99.0,
+            24 * 3600,
+            SloIndicator::Ratio { good: query("requests_ok"), total: query("requests_total") },
+        )
+    }
+
+    fn threshold(rule: &AlertRule) -> f64 {
+        match rule.condition {
+            AlertCondition::Threshold { threshold, .. } => threshold,
+            _ => panic!("burn-rate rules are thresholds"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_budget_math_at_window_boundaries() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 2, 12, 0, 0).unwrap();
+        let window_start = now - chrono::Duration::hours(24);
+        let metrics = Arc::new(SeriesMetrics::default());
+        {
+            let mut points = metrics.points.lock().unwrap();
+            // Exactly one window old: outside the rolling window and therefore ignored
+            points.push(point("requests_total", window_start, 1000.0));
+            points.push(point("requests_ok", window_start, 0.0));
+            // One second into the window, 10 of 1000 bad: half of a 1% budget
+            points.push(point("requests_total", window_start + chrono::Duration::seconds(1), 1000.0));
+            points.push(point("requests_ok", window_start + chrono::Duration::seconds(1), 995.0));
+            // At `now`, inside both burn windows: 5 of 1000 bad
+            points.push(point("requests_total", now, 1000.0));
+            points.push(point("requests_ok", now, 995.0));
+        }
+        let manager = SloManager::new(metrics.clone(), Arc::new(RuleStore::default()));
+        manager.create_slo(availability()).await.unwrap();
+
+        let status = manager.evaluate("api-availability", now).await.unwrap();
+        assert_eq!(status.total_events, 2000.0);
+        assert_eq!(status.good_events, 1990.0);
+        assert!((status.sli.unwrap() - 99.5).abs() < 1e-9);
+        assert!((status.error_budget_remaining - 0.5).abs() < 1e-9);
+        // Only the sample at `now` falls in the 1h and 6h windows: 0.5% errors against a 1% budget
+        assert!((status.fast_burn.burn_rate - 0.5).abs() < 1e-9);
+        assert!((status.slow_burn.error_rate - 0.005).abs() < 1e-9);
+
+        // A second later the first in-window sample ages out too
+        let later = manager
+            .evaluate("api-availability", now + chrono::Duration::seconds(1))
+            .await
+            .unwrap();
+        assert_eq!(later.total_events, 1000.0);
+        assert!((later.error_budget_remaining - 0.5).abs() < 1e-9);
+
+        // The whole budget gone and then some
+        metrics.points.lock().unwrap().push(point("requests_total", now, 1000.0));
+        let breached = manager.evaluate("api-availability", now).await.unwrap();
+        assert!((breached.error_budget_remaining - (1.0 - 1010.0 / 30.0)).abs() < 1e-9);
+
+        let report = manager.get_slo_status("api-availability").await.unwrap();
+        assert_eq!(report.history.len(), 3);
+        assert_eq!(report.current.unwrap().total_events, 3000.0);
+        let published = metrics.points.lock().unwrap().iter().filter(|p| p.namespace == SLO_NAMESPACE).count();
+        assert_eq!(published, 9);
+    }
+
+    #[tokio::test]
+    async fn test_target_edit_regenerates_alert_thresholds() {
+        let alerts = Arc::new(RuleStore::default());
+        let manager = SloManager::new(Arc::new(SeriesMetrics::default()), alerts.clone());
+        let slo = manager.create_slo(availability()).await.unwrap();
+
+        let fast = alerts.get_alert_rule(&slo.fast_burn_rule_id()).await.unwrap();
+        let slow = alerts.get_alert_rule(&slo.slow_burn_rule_id()).await.unwrap();
+        assert!((threshold(&fast) - 0.144).abs() < 1e-9);
+        assert!((threshold(&slow) - 0.06).abs() < 1e-9);
+        assert_eq!(fast.query.dimensions.as_ref().unwrap()[DIM_WINDOW], "1h");
+        assert_eq!(slow.query.dimensions.as_ref().unwrap()[DIM_WINDOW], "6h");
+
+        let mut tightened = slo.clone();
+        tightened.target = 99.9;
+        // A rule deleted out from under the SLO comes back on the next edit
+        alerts.delete_alert_rule(&slo.slow_burn_rule_id()).await.unwrap();
+        manager.update_slo(tightened).await.unwrap();
+        let fast = alerts.get_alert_rule(&slo.fast_burn_rule_id()).await.unwrap();
+        let slow = alerts.get_alert_rule(&slo.slow_burn_rule_id()).await.unwrap();
+        assert!((threshold(&fast) - 0.0144).abs() < 1e-9);
+        assert!((threshold(&slow) - 0.006).abs() < 1e-9);
+
+        let mut invalid = slo.clone();
+        invalid.target = 100.0;
+        assert!(manager.update_slo(invalid).await.is_err());
+        assert!(manager.create_slo(availability()).await.is_err());
+
+        manager.delete_slo(&slo.id).await.unwrap();
+        assert!(alerts.list_alert_rules().await.unwrap().is_empty());
+        assert!(manager.get_slo_status(&slo.id).await.is_err());
+    }
+}
@@ -0,0 +1,619 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use sirsi_common::RetryPolicy;
+use sirsi_key_vault::secret::{SecretManager, SecretValue};
+use sirsi_key_vault::KeyVaultError;
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use super::{AlertEvent, NotificationChannel, NotificationType};
+use crate::error::{ObservabilityError, ObservabilityResult};
+
+pub const SIGNATURE_HEADER: &str = "X-Sirsi-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-Sirsi-Timestamp";
+pub const DELIVERY_HEADER: &str = "X-Sirsi-Delivery";
+
+const URL_SETTING: &str = "url";
+const SECRET_SETTING: &str = "secret_ref";
+
+type HmacSha256 = Hmac<Sha256>;
+
+fn signed_payload(timestamp: i64, body: &[u8]) -> Vec<u8> {
+    let mut payload = format!("{}.", timestamp).into_bytes();
+    payload.extend_from_slice(body);
+    payload
+}
+
+// The timestamp is part of the signed payload so a captured request can't be replayed
+// later under a fresh timestamp header
+pub fn sign(secret: &[u8], timestamp: i64, body: &[u8]) -> String {
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(&signed_payload(timestamp, body));
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+// Receiver-side check: constant-time signature comparison plus a freshness window
+pub fn verify(secret: &[u8], timestamp: i64, body: &[u8], signature: &str, now: DateTime<Utc>, tolerance: Duration) -> bool {
+    let age = (now.timestamp() - timestamp).unsigned_abs();
+    if age > tolerance.as_secs() {
+        return false;
+    }
+    let Some(expected) = signature.strip_prefix("sha256=").and_then(|hex| hex::decode(hex).ok()) else {
+        return false;
+    };
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(&signed_payload(timestamp, body));
+    mac.verify_slice(&expected).is_ok()
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WebhookSettings {
+    pub url: String,
+    // Key-vault secret id holding the channel's signing secret
+    pub secret_ref: String,
+}
+
+impl WebhookSettings {
+    pub fn from_channel(channel: &NotificationChannel) -> ObservabilityResult<Self> {
+        if !matches!(channel.channel_type, NotificationType::Webhook) {
+            return Err(ObservabilityError::Validation(format!(
+                "Notification channel {} is not a webhook",
+                channel.id
+            )));
+        }
+        let setting = |key: &str| {
+            channel
+                .settings
+                .get(key)
+                .filter(|v| !v.is_empty())
+                .cloned()
+                .ok_or_else(|| ObservabilityError::Config(format!("Webhook channel {} has no {} setting", channel.id, key)))
+        };
+        Ok(Self {
+            url: setting(URL_SETTING)?,
+            secret_ref: setting(SECRET_SETTING)?,
+        })
+    }
+}
+
+#[async_trait]
+pub trait SecretResolver: Send + Sync {
+    async fn resolve(&self, reference: &str) -> ObservabilityResult<String>;
+}
+
+// Resolves webhook signing secrets from the key vault; only plain-text secrets are usable here
+pub struct KeyVaultSecrets {
+    manager: Arc<dyn SecretManager>,
+}
+
+impl KeyVaultSecrets {
+    pub fn new(manager: Arc<dyn SecretManager>) -> Self {
+        Self { manager }
+    }
+}
+
+#[async_trait]
+impl SecretResolver for KeyVaultSecrets {
+    async fn resolve(&self, reference: &str) -> ObservabilityResult<String> {
+        let secret = self.manager.get_secret(reference).await.map_err(|e| match e {
+            KeyVaultError::NotFound(_) => ObservabilityError::Config(format!("Secret {} not found", reference)),
+            KeyVaultError::Permission(msg) => ObservabilityError::Auth(msg),
+            other => ObservabilityError::Internal(format!("Failed to read secret {}: {}", reference, other)),
+        })?;
+        match secret.value {
+            SecretValue::Plain(value) => Ok(value),
+            _ => Err(ObservabilityError::Config(format!("Secret {} is not a plain-text value", reference))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebhookRequest {
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl WebhookRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+#[async_trait]
+pub trait WebhookTransport: Send + Sync {
+    // Returns the receiver's HTTP status; Err only when no response arrived
+    async fn post(&self, request: &WebhookRequest) -> ObservabilityResult<u16>;
+}
+
+pub struct HttpWebhookTransport {
+    client: reqwest::Client,
+}
+
+impl HttpWebhookTransport {
+    pub fn new(timeout: Duration) -> ObservabilityResult<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .build()
+            .map_err(|e| ObservabilityError::Config(format!("Failed to build webhook client: {}", e)))?;
+        Ok(Self { client })
+    }
+}
+
+#[async_trait]
+impl WebhookTransport for HttpWebhookTransport {
+    async fn post(&self, request: &WebhookRequest) -> ObservabilityResult<u16> {
+        let mut builder = self.client.post(&request.url).body(request.body.clone());
+        for (name, value) in &request.headers {
+            builder = builder.header(name, value);
+        }
+        let response = builder
+            .send()
+            .await
+            .map_err(|e| ObservabilityError::Unavailable(format!("Webhook POST to {} failed: {}", request.url, e)))?;
+        Ok(response.status().as_u16())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DeliveryStatus {
+    Pending,
+    Delivered,
+    Retrying,
+    // Retries or the max age ran out
+    Failed,
+    // The receiver answered 410 Gone; no further automatic attempts
+    Gone,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookDelivery {
+    pub id: String,
+    pub channel_id: String,
+    pub event_id: String,
+    pub url: String,
+    pub secret_ref: String,
+    pub body: String,
+    pub status: DeliveryStatus,
+    pub attempts: u32,
+    pub created_at: DateTime<Utc>,
+    pub last_attempt_at: Option<DateTime<Utc>>,
+    pub next_attempt_at: Option<DateTime<Utc>>,
+    pub last_status_code: Option<u16>,
+    pub last_error: Option<String>,
+    pub redelivery_of: Option<String>,
+}
+
+#[async_trait]
+pub trait WebhookDeliveryStore: Send + Sync {
+    async fn save_delivery(&self, delivery: &WebhookDelivery) -> ObservabilityResult<()>;
+    async fn get_delivery(&self, id: &str) -> ObservabilityResult<Option<WebhookDelivery>>;
+    async fn list_deliveries(&self, channel_id: &str) -> ObservabilityResult<Vec<WebhookDelivery>>;
+    async fn due_deliveries(&self, now: DateTime<Utc>) -> ObservabilityResult<Vec<WebhookDelivery>>;
+}
+
+#[derive(Default)]
+pub struct InMemoryWebhookDeliveryStore {
+    deliveries: RwLock<HashMap<String, WebhookDelivery>>,
+}
+
+impl InMemoryWebhookDeliveryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl WebhookDeliveryStore for InMemoryWebhookDeliveryStore {
+    async fn save_delivery(&self, delivery: &WebhookDelivery) -> ObservabilityResult<()> {
+        self.deliveries.write().await.insert(delivery.id.clone(), delivery.clone());
+        Ok(())
+    }
+
+    async fn get_delivery(&self, id: &str) -> ObservabilityResult<Option<WebhookDelivery>> {
+        Ok(self.deliveries.read().await.get(id).cloned())
+    }
+
+    async fn list_deliveries(&self, channel_id: &str) -> ObservabilityResult<Vec<WebhookDelivery>> {
+        let mut deliveries: Vec<WebhookDelivery> = self
+            .deliveries
+            .read()
+            .await
+            .values()
+            .filter(|d| d.channel_id == channel_id)
+            .cloned()
+            .collect();
+        deliveries.sort_by_key(|d| d.created_at);
+        Ok(deliveries)
+    }
+
+    async fn due_deliveries(&self, now: DateTime<Utc>) -> ObservabilityResult<Vec<WebhookDelivery>> {
+        let mut due: Vec<WebhookDelivery> = self
+            .deliveries
+            .read()
+            .await
+            .values()
+            .filter(|d| d.status == DeliveryStatus::Retrying && d.next_attempt_at.is_some_and(|at| at <= now))
+            .cloned()
+            .collect();
+        due.sort_by_key(|d| d.next_attempt_at);
+        Ok(due)
+    }
+}
+
+pub struct WebhookSender {
+    transport: Arc<dyn WebhookTransport>,
+    secrets: Arc<dyn SecretResolver>,
+    store: Arc<dyn WebhookDeliveryStore>,
+    retry_policy: RetryPolicy,
+    // Deliveries older than this are not retried regardless of attempts left
+    max_age: Duration,
+}
+
+impl WebhookSender {
+    pub fn new(transport: Arc<dyn WebhookTransport>, secrets: Arc<dyn SecretResolver>) -> Self {
+        Self {
+            transport,
+            secrets,
+            store: Arc::new(InMemoryWebhookDeliveryStore::new()),
+            retry_policy: RetryPolicy::new(8).with_backoff(Duration::from_secs(30), Duration::from_secs(3600)),
+            max_age: Duration::from_secs(24 * 3600),
+        }
+    }
+
+    pub fn with_store(mut self, store: Arc<dyn WebhookDeliveryStore>) -> Self {
+        self.store = store;
+        self
+    }
+
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    pub async fn send(
+        &self,
+        channel: &NotificationChannel,
+        event: &AlertEvent,
+        now: DateTime<Utc>,
+    ) -> ObservabilityResult<WebhookDelivery> {
+        if !channel.enabled {
+            return Err(ObservabilityError::Validation(format!(
+                "Notification channel {} is disabled",
+                channel.id
+            )));
+        }
+        let settings = WebhookSettings::from_channel(channel)?;
+        let body = serde_json::to_string(event)
+            .map_err(|e| ObservabilityError::Internal(format!("Failed to encode alert event: {}", e)))?;
+        let delivery = WebhookDelivery {
+            id: uuid::Uuid::new_v4().to_string(),
+            channel_id: channel.id.clone(),
+            event_id: event.id.clone(),
+            url: settings.url,
+            secret_ref: settings.secret_ref,
+            body,
+            status: DeliveryStatus::Pending,
+            attempts: 0,
+            created_at: now,
+            last_attempt_at: None,
+            next_attempt_at: None,
+            last_status_code: None,
+            last_error: None,
+            redelivery_of: None,
+        };
+        self.store.save_delivery(&delivery).await?;
+        self.attempt(delivery, now).await
+    }
+
+    async fn attempt(&self, mut delivery: WebhookDelivery, now: DateTime<Utc>) -> ObservabilityResult<WebhookDelivery> {
+        delivery.attempts += 1;
+        delivery.last_attempt_at = Some(now);
+        delivery.next_attempt_at = None;
+
+        let outcome = match self.secrets.resolve(&delivery.secret_ref).await {
+            Ok(secret) => {
+                let timestamp = now.timestamp();
+                let request = WebhookRequest {
+                    url: delivery.url.clone(),
+                    headers: vec![
+                        ("Content-Type".to_string(), "application/json".to_string()),
+                        (SIGNATURE_HEADER.to_string(), sign(secret.as_bytes(), timestamp, delivery.body.as_bytes())),
+                        (TIMESTAMP_HEADER.to_string(), timestamp.to_string()),
+                        (DELIVERY_HEADER.to_string(), delivery.id.clone()),
+                    ],
+                    body: delivery.body.clone().into_bytes(),
+                };
+                self.transport.post(&request).await
+            }
+            Err(e) => Err(e),
+        };
+
+        match outcome {
+            Ok(status) if (200..300).contains(&status) => {
+                delivery.status = DeliveryStatus::Delivered;
+                delivery.last_status_code = Some(status);
+                delivery.last_error = None;
+            }
+            Ok(410) => {
+                delivery.status = DeliveryStatus::Gone;
+                delivery.last_status_code = Some(410);
+                delivery.last_error = Some("Receiver responded 410 Gone".to_string());
+                warn!("Webhook {} for channel {} is gone; retries stopped", delivery.id, delivery.channel_id);
+            }
+            Ok(status) => {
+                delivery.last_status_code = Some(status);
+                delivery.last_error = Some(format!("Receiver responded {}", status));
+                self.schedule_retry(&mut delivery, now);
+            }
+            Err(e) => {
+                delivery.last_status_code = None;
+                delivery.last_error = Some(e.to_string());
+                self.schedule_retry(&mut delivery, now);
+            }
+        }
+        self.store.save_delivery(&delivery).await?;
+        Ok(delivery)
+    }
+
+    fn schedule_retry(&self, delivery: &mut WebhookDelivery, now: DateTime<Utc>) {
+        let cutoff = delivery.created_at + chrono::Duration::from_std(self.max_age).unwrap_or(chrono::Duration::MAX);
+        let next = chrono::Duration::from_std(self.retry_policy.backoff_for(delivery.attempts))
+            .ok()
+            .and_then(|delay| now.checked_add_signed(delay));
+        match next {
+            Some(next) if delivery.attempts < self.retry_policy.max_attempts && next <= cutoff => {
+                delivery.status = DeliveryStatus::Retrying;
+                delivery.next_attempt_at = Some(next);
+            }
+            _ => {
+                delivery.status = DeliveryStatus::Failed;
+                warn!(
+                    "Webhook {} for channel {} failed after {} attempts: {}",
+                    delivery.id,
+                    delivery.channel_id,
+                    delivery.attempts,
+                    delivery.last_error.as_deref().unwrap_or_default()
+                );
+            }
+        }
+    }
+
+    pub async fn retry_due(&self, now: DateTime<Utc>) -> ObservabilityResult<Vec<WebhookDelivery>> {
+        let mut attempted = Vec::new();
+        for delivery in self.store.due_deliveries(now).await? {
+            attempted.push(self.attempt(delivery, now).await?);
+        }
+        Ok(attempted)
+    }
+
+    // Manual replay: a new delivery of the same payload, whatever happened to the original
+    pub async fn redeliver(&self, delivery_id: &str, now: DateTime<Utc>) -> ObservabilityResult<WebhookDelivery> {
+        let original = self
+            .store
+            .get_delivery(delivery_id)
+            .await?
+            .ok_or_else(|| ObservabilityError::NotFound(format!("Webhook delivery {} not found", delivery_id)))?;
+        info!("Redelivering webhook {} for channel {}", original.id, original.channel_id);
+        let delivery = WebhookDelivery {
+            id: uuid::Uuid::new_v4().to_string(),
+            status: DeliveryStatus::Pending,
+            attempts: 0,
+            created_at: now,
+            last_attempt_at: None,
+            next_attempt_at: None,
+            last_status_code: None,
+            last_error: None,
+            redelivery_of: Some(original.id.clone()),
+            ..original
+        };
+        self.store.save_delivery(&delivery).await?;
+        self.attempt(delivery, now).await
+    }
+
+    pub async fn get_delivery(&self, delivery_id: &str) -> ObservabilityResult<Option<WebhookDelivery>> {
+        self.store.get_delivery(delivery_id).await
+    }
+
+    pub async fn list_deliveries(&self, channel_id: &str) -> ObservabilityResult<Vec<WebhookDelivery>> {
+        self.store.list_deliveries(channel_id).await
+    }
+
+    pub fn spawn_retry_worker(self: Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = self.retry_due(Utc::now()).await {
+                    warn!("Webhook retry pass failed: {}", e);
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{AlertSeverity, AlertState};
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct MockReceiver {
+        responses: Mutex<VecDeque<u16>>,
+        requests: Mutex<Vec<WebhookRequest>>,
+    }
+
+    impl MockReceiver {
+        fn respond(statuses: &[u16]) -> Arc<Self> {
+            Arc::new(Self {
+                responses: Mutex::new(statuses.iter().copied().collect()),
+                requests: Mutex::new(Vec::new()),
+            })
+        }
+
+        fn requests(&self) -> Vec<WebhookRequest> {
+            self.requests.lock().unwrap().clone()
+        }
+    }
+
+    #[async_trait]
+    impl WebhookTransport for MockReceiver {
+        async fn post(&self, request: &WebhookRequest) -> ObservabilityResult<u16> {
+            self.requests.lock().unwrap().push(request.clone());
+            match self.responses.lock().unwrap().pop_front() {
+                Some(0) | None => Err(ObservabilityError::Unavailable("connection refused".to_string())),
+                Some(status) => Ok(status),
+            }
+        }
+    }
+
+    struct StaticSecrets;
+
+    #[async_trait]
+    impl SecretResolver for StaticSecrets {
+        async fn resolve(&self, reference: &str) -> ObservabilityResult<String> {
+            match reference {
+                "webhooks/ops" => Ok("s3cret".to_string()),
+                other => Err(ObservabilityError::Config(format!("Secret {} not found", other))),
+            }
+        }
+    }
+
+    fn channel() -> NotificationChannel {
+        NotificationChannel {
+            id: "ops-webhook".to_string(),
+            name: "Ops".to_string(),
+            channel_type: NotificationType::Webhook,
+            settings: HashMap::from([
+                (URL_SETTING.to_string(), "https://hooks.example.com/alerts".to_string()),
+                (SECRET_SETTING.to_string(), "webhooks/ops".to_string()),
+            ]),
+            enabled: true,
+        }
+    }
+
+    fn event() -> AlertEvent {
+        AlertEvent {
+            id: "evt-1".to_string(),
+            rule_id: "cpu-high".to_string(),
+            severity: AlertSeverity::Critical,
+            state: AlertState::Firing,
+            message: "CPU above 95%".to_string(),
+            value: 97.0,
+            timestamp: Utc::now(),
+            resolved_at: None,
+            metadata: HashMap::new(),
+            actions: Vec::new(),
+        }
+    }
+
+    fn sender(receiver: Arc<MockReceiver>) -> WebhookSender {
+        WebhookSender::new(receiver, Arc::new(StaticSecrets))
+            .with_retry_policy(RetryPolicy::new(4).with_backoff(Duration::from_secs(30), Duration::from_secs(600)))
+    }
+
+    #[tokio::test]
+    async fn test_signature_headers() {
+        let mut mac = HmacSha256::new_from_slice(b"Jefe").unwrap();
+        mac.update(b"what do ya want for nothing?");
+        assert_eq!(
+            hex::encode(mac.finalize().into_bytes()),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+
+        let receiver = MockReceiver::respond(&[200]);
+        let now = Utc::now();
+        let delivery = sender(receiver.clone()).send(&channel(), &event(), now).await.unwrap();
+        assert_eq!(delivery.status, DeliveryStatus::Delivered);
+
+        let request = &receiver.requests()[0];
+        let timestamp: i64 = request.header(TIMESTAMP_HEADER).unwrap().parse().unwrap();
+        let signature = request.header(SIGNATURE_HEADER).unwrap();
+        assert_eq!(timestamp, now.timestamp());
+        assert_eq!(signature, sign(b"s3cret", timestamp, &request.body));
+        assert_eq!(request.header(DELIVERY_HEADER), Some(delivery.id.as_str()));
+
+        let tolerance = Duration::from_secs(300);
+        assert!(verify(b"s3cret", timestamp, &request.body, signature, now, tolerance));
+        assert!(!verify(b"wrong", timestamp, &request.body, signature, now, tolerance));
+        assert!(!verify(b"s3cret", timestamp, b"{}", signature, now, tolerance));
+        let replayed = now + chrono::Duration::minutes(10);
+        assert!(!verify(b"s3cret", timestamp, &request.body, signature, replayed, tolerance));
+    }
+
+    #[tokio::test]
+    async fn test_retry_schedule() {
+        let receiver = MockReceiver::respond(&[503, 0, 200]);
+        let sender = sender(receiver.clone());
+        let start = Utc::now();
+
+        let delivery = sender.send(&channel(), &event(), start).await.unwrap();
+        assert_eq!(delivery.status, DeliveryStatus::Retrying);
+        assert_eq!(delivery.last_status_code, Some(503));
+        assert_eq!(delivery.next_attempt_at, Some(start + chrono::Duration::seconds(30)));
+
+        assert!(sender.retry_due(start + chrono::Duration::seconds(29)).await.unwrap().is_empty());
+        let second = start + chrono::Duration::seconds(30);
+        let retried = sender.retry_due(second).await.unwrap().remove(0);
+        assert_eq!(retried.attempts, 2);
+        assert!(retried.last_error.as_deref().unwrap().contains("connection refused"));
+        assert_eq!(retried.next_attempt_at, Some(second + chrono::Duration::seconds(60)));
+
+        let third = second + chrono::Duration::seconds(60);
+        let delivered = sender.retry_due(third).await.unwrap().remove(0);
+        assert_eq!(delivered.status, DeliveryStatus::Delivered);
+        assert_eq!(delivered.attempts, 3);
+        assert_eq!(receiver.requests().len(), 3);
+
+        // A retry that would land past the max age fails the delivery instead
+        let aged = WebhookSender::new(MockReceiver::respond(&[500]), Arc::new(StaticSecrets))
+            .with_max_age(Duration::from_secs(20));
+        let failed = aged.send(&channel(), &event(), start).await.unwrap();
+        assert_eq!(failed.status, DeliveryStatus::Failed);
+        assert_eq!(aged.list_deliveries("ops-webhook").await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_gone_stops_retries() {
+        let receiver = MockReceiver::respond(&[410, 200]);
+        let sender = sender(receiver.clone());
+        let start = Utc::now();
+
+        let delivery = sender.send(&channel(), &event(), start).await.unwrap();
+        assert_eq!(delivery.status, DeliveryStatus::Gone);
+        assert_eq!(delivery.next_attempt_at, None);
+        assert!(sender.retry_due(start + chrono::Duration::days(1)).await.unwrap().is_empty());
+        assert_eq!(receiver.requests().len(), 1);
+
+        let replay = sender.redeliver(&delivery.id, start + chrono::Duration::hours(1)).await.unwrap();
+        assert_eq!(replay.status, DeliveryStatus::Delivered);
+        assert_eq!(replay.redelivery_of.as_deref(), Some(delivery.id.as_str()));
+        assert_eq!(receiver.requests()[1].body, receiver.requests()[0].body);
+        assert_eq!(
+            sender.get_delivery(&delivery.id).await.unwrap().unwrap().status,
+            DeliveryStatus::Gone
+        );
+        assert!(matches!(
+            sender.redeliver("missing", start).await,
+            Err(ObservabilityError::NotFound(_))
+        ));
+    }
+}
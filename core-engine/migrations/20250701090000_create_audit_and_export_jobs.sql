@@ -0,0 +1,27 @@
+-- Audit trail of changes made through the API, scoped to the owning tenant
+CREATE TABLE IF NOT EXISTS audit_events (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    owner_id UUID NOT NULL REFERENCES users(id),
+    actor_id UUID NOT NULL REFERENCES users(id),
+    action STRING NOT NULL,
+    target_type STRING NOT NULL,
+    target_id UUID,
+    details JSONB NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    INDEX audit_events_owner_idx (owner_id, created_at)
+);
+
+-- Exports too large to stream inline are written to object storage in the background
+CREATE TABLE IF NOT EXISTS export_jobs (
+    id UUID PRIMARY KEY,
+    owner_id UUID NOT NULL REFERENCES users(id),
+    kind STRING NOT NULL,
+    format STRING NOT NULL,
+    status STRING NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'running', 'completed', 'failed')),
+    object_key STRING,
+    row_count INT8,
+    error STRING,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    completed_at TIMESTAMPTZ,
+    INDEX export_jobs_owner_idx (owner_id)
+);
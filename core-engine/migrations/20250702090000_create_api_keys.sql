@@ -0,0 +1,15 @@
+-- API keys for service-to-service callers; only a SHA-256 hash of the key is kept
+CREATE TABLE IF NOT EXISTS api_keys (
+    id UUID PRIMARY KEY,
+    owner_id UUID NOT NULL REFERENCES users(id),
+    created_by UUID NOT NULL REFERENCES users(id),
+    name STRING NOT NULL,
+    prefix STRING NOT NULL UNIQUE,
+    key_hash STRING NOT NULL,
+    scopes STRING[] NOT NULL,
+    expires_at TIMESTAMPTZ,
+    last_used_at TIMESTAMPTZ,
+    revoked_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    INDEX api_keys_owner_idx (owner_id)
+);
@@ -0,0 +1,4 @@
+-- Row versions for optimistic concurrency: every update bumps the version and only
+-- applies if the caller's expected version still matches
+ALTER TABLE projects ADD COLUMN IF NOT EXISTS version INT8 NOT NULL DEFAULT 1;
+ALTER TABLE resources ADD COLUMN IF NOT EXISTS version INT8 NOT NULL DEFAULT 1;
@@ -0,0 +1,14 @@
+-- Directed dependencies between resources: `source` depends on `target` (an instance uses
+-- a security group, which uses a VPC)
+CREATE TABLE IF NOT EXISTS resource_links (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    owner_id UUID NOT NULL REFERENCES users(id),
+    source_id UUID NOT NULL REFERENCES resources(id) ON DELETE CASCADE,
+    target_id UUID NOT NULL REFERENCES resources(id) ON DELETE CASCADE,
+    link_type STRING NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE INDEX resource_links_edge_idx (source_id, target_id, link_type),
+    INDEX resource_links_target_idx (target_id),
+    INDEX resource_links_owner_idx (owner_id),
+    CHECK (source_id != target_id)
+);
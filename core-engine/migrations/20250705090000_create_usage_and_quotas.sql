@@ -0,0 +1,41 @@
+-- Billable events per tenant per UTC day
+CREATE TABLE IF NOT EXISTS usage_daily (
+    tenant_id UUID NOT NULL REFERENCES users(id),
+    day DATE NOT NULL,
+    event STRING NOT NULL,
+    count INT8 NOT NULL DEFAULT 0,
+    PRIMARY KEY (tenant_id, day, event),
+    INDEX usage_daily_day_idx (day)
+);
+
+-- Per-tenant overrides of the default quota limits; NULL means unlimited
+CREATE TABLE IF NOT EXISTS tenant_quotas (
+    tenant_id UUID PRIMARY KEY REFERENCES users(id),
+    max_projects INT8,
+    max_concurrent_workflow_runs INT8,
+    max_functions INT8,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Quota counters; only ever changed by a conditional update, which keeps concurrent
+-- creates from overshooting
+CREATE TABLE IF NOT EXISTS quota_usage (
+    tenant_id UUID NOT NULL REFERENCES users(id),
+    quota STRING NOT NULL,
+    used INT8 NOT NULL DEFAULT 0 CHECK (used >= 0),
+    PRIMARY KEY (tenant_id, quota)
+);
+
+-- What each counted unit is held by, for quotas counted per name (functions)
+CREATE TABLE IF NOT EXISTS quota_holdings (
+    tenant_id UUID NOT NULL REFERENCES users(id),
+    quota STRING NOT NULL,
+    holder STRING NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (tenant_id, quota, holder)
+);
+
+-- Existing projects count against the new quota
+INSERT INTO quota_usage (tenant_id, quota, used)
+SELECT owner_id, 'max_projects', COUNT(*) FROM projects GROUP BY owner_id
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,24 @@
+-- One row per completed discovery of a tenant's provider account
+CREATE TABLE IF NOT EXISTS discovery_runs (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    tenant_id UUID NOT NULL REFERENCES users(id),
+    provider STRING NOT NULL,
+    account_id STRING NOT NULL,
+    resource_count INT8 NOT NULL DEFAULT 0,
+    started_at TIMESTAMPTZ NOT NULL,
+    completed_at TIMESTAMPTZ NOT NULL,
+    INDEX discovery_runs_account_idx (tenant_id, provider, account_id, completed_at DESC),
+    INDEX discovery_runs_tenant_idx (tenant_id, completed_at DESC)
+);
+
+-- Normalized resources seen by a run; the primary key doubles as the sort order diffs merge on
+CREATE TABLE IF NOT EXISTS discovery_run_resources (
+    run_id UUID NOT NULL REFERENCES discovery_runs(id) ON DELETE CASCADE,
+    resource_key STRING NOT NULL,
+    region STRING NOT NULL,
+    resource_type STRING NOT NULL,
+    name STRING,
+    tags JSONB NOT NULL DEFAULT '{}',
+    attributes JSONB NOT NULL DEFAULT '{}',
+    PRIMARY KEY (run_id, resource_key, region)
+);
@@ -0,0 +1,38 @@
+-- Scheduled digests; sections, schedule and recipients are small JSON documents
+CREATE TABLE IF NOT EXISTS report_definitions (
+    id UUID PRIMARY KEY,
+    tenant_id UUID NOT NULL REFERENCES users(id),
+    project_id UUID REFERENCES projects(id) ON DELETE CASCADE,
+    name STRING NOT NULL,
+    sections JSONB NOT NULL,
+    schedule JSONB NOT NULL,
+    recipients JSONB NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    -- The schedule slot last claimed by a scheduler, so each slot runs once
+    last_scheduled_for TIMESTAMPTZ,
+    INDEX report_definitions_tenant_idx (tenant_id, created_at)
+);
+
+CREATE TABLE IF NOT EXISTS report_runs (
+    id UUID PRIMARY KEY,
+    report_id UUID NOT NULL REFERENCES report_definitions(id) ON DELETE CASCADE,
+    tenant_id UUID NOT NULL REFERENCES users(id),
+    trigger STRING NOT NULL,
+    period_start TIMESTAMPTZ NOT NULL,
+    period_end TIMESTAMPTZ NOT NULL,
+    status STRING NOT NULL,
+    error STRING,
+    deliveries JSONB NOT NULL DEFAULT '[]',
+    artifacts JSONB NOT NULL DEFAULT '[]',
+    created_at TIMESTAMPTZ NOT NULL,
+    INDEX report_runs_report_idx (tenant_id, report_id, created_at DESC)
+);
+
+-- Rendered HTML and CSV files, kept so past runs can be downloaded again
+CREATE TABLE IF NOT EXISTS report_run_artifacts (
+    run_id UUID NOT NULL REFERENCES report_runs(id) ON DELETE CASCADE,
+    name STRING NOT NULL,
+    content_type STRING NOT NULL,
+    content BYTES NOT NULL,
+    PRIMARY KEY (run_id, name)
+);
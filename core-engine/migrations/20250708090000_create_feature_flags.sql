@@ -0,0 +1,15 @@
+-- Feature flags; targeting rules and the fallthrough are small JSON documents.
+-- Changes are recorded in audit_events under target_type 'flag'.
+CREATE TABLE IF NOT EXISTS feature_flags (
+    id UUID PRIMARY KEY,
+    key STRING NOT NULL UNIQUE,
+    description STRING NOT NULL DEFAULT '',
+    kind STRING NOT NULL,
+    enabled BOOL NOT NULL,
+    bucket_by STRING NOT NULL DEFAULT 'subject',
+    rules JSONB NOT NULL DEFAULT '[]',
+    fallthrough JSONB NOT NULL,
+    version INT8 NOT NULL DEFAULT 1,
+    updated_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    updated_at TIMESTAMPTZ NOT NULL
+);
@@ -0,0 +1,13 @@
+-- Admin impersonation sessions; the session id is the jti of the token handed out
+CREATE TABLE IF NOT EXISTS impersonation_sessions (
+    id UUID PRIMARY KEY,
+    actor_id UUID NOT NULL REFERENCES users(id),
+    target_id UUID NOT NULL REFERENCES users(id),
+    reason STRING NOT NULL,
+    started_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at TIMESTAMPTZ NOT NULL,
+    revoked_at TIMESTAMPTZ,
+    revoked_by UUID REFERENCES users(id),
+    INDEX impersonation_sessions_target_idx (target_id, started_at DESC),
+    INDEX impersonation_sessions_actor_idx (actor_id, started_at DESC)
+);
@@ -0,0 +1,58 @@
+-- Organizations own projects; access to a project comes from the member's role in its org
+CREATE TABLE IF NOT EXISTS organizations (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name STRING NOT NULL,
+    -- Each user's default org for projects created without naming one
+    personal_for UUID UNIQUE REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS organization_members (
+    org_id UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    role STRING NOT NULL CHECK (role IN ('admin', 'billing_admin', 'member')),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (org_id, user_id),
+    INDEX organization_members_user_idx (user_id)
+);
+
+-- Only a SHA-256 hash of the emailed token is kept
+CREATE TABLE IF NOT EXISTS organization_invitations (
+    id UUID PRIMARY KEY,
+    org_id UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    email STRING NOT NULL,
+    role STRING NOT NULL CHECK (role IN ('admin', 'billing_admin', 'member')),
+    token_hash STRING NOT NULL UNIQUE,
+    invited_by UUID NOT NULL REFERENCES users(id),
+    expires_at TIMESTAMPTZ NOT NULL,
+    accepted_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    INDEX organization_invitations_org_idx (org_id)
+);
+
+-- Existing projects move into a personal org for their owner, who becomes its admin
+INSERT INTO organizations (id, name, personal_for)
+SELECT gen_random_uuid(), COALESCE(u.name, u.email), u.id
+FROM users u
+WHERE EXISTS (SELECT 1 FROM projects p WHERE p.owner_id = u.id)
+ON CONFLICT (personal_for) DO NOTHING;
+
+INSERT INTO organization_members (org_id, user_id, role)
+SELECT id, personal_for, 'admin' FROM organizations WHERE personal_for IS NOT NULL
+ON CONFLICT DO NOTHING;
+
+ALTER TABLE projects ADD COLUMN IF NOT EXISTS org_id UUID REFERENCES organizations(id);
+
+UPDATE projects SET org_id = o.id
+FROM organizations o
+WHERE o.personal_for = projects.owner_id AND projects.org_id IS NULL;
+
+ALTER TABLE projects ALTER COLUMN org_id SET NOT NULL;
+CREATE INDEX IF NOT EXISTS projects_org_idx ON projects (org_id);
+
+-- The org is now the tenant for project quota, so the counters are keyed by org id
+ALTER TABLE quota_usage DROP CONSTRAINT IF EXISTS quota_usage_tenant_id_fkey;
+ALTER TABLE tenant_quotas DROP CONSTRAINT IF EXISTS tenant_quotas_tenant_id_fkey;
+DELETE FROM quota_usage WHERE quota = 'max_projects';
+INSERT INTO quota_usage (tenant_id, quota, used)
+SELECT org_id, 'max_projects', COUNT(*) FROM projects GROUP BY org_id;
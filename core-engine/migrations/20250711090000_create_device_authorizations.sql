@@ -0,0 +1,14 @@
+-- Device authorization grant (RFC 8628); only a hash of the device code is kept
+CREATE TABLE IF NOT EXISTS device_authorizations (
+    id UUID PRIMARY KEY,
+    device_code_hash STRING NOT NULL UNIQUE,
+    user_code STRING NOT NULL,
+    client_id STRING NOT NULL,
+    status STRING NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'approved', 'denied', 'consumed')),
+    user_id UUID REFERENCES users(id) ON DELETE CASCADE,
+    interval_secs INT8 NOT NULL,
+    last_polled_at TIMESTAMPTZ,
+    expires_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    INDEX device_authorizations_user_code_idx (user_code, status)
+);
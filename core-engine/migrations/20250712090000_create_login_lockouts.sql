@@ -0,0 +1,9 @@
+-- Failed sign-in counters keyed by account (account:<email>) or client address (ip:<addr>)
+CREATE TABLE IF NOT EXISTS login_lockouts (
+    key STRING PRIMARY KEY,
+    failures INT8 NOT NULL DEFAULT 0,
+    last_failure_at TIMESTAMPTZ NOT NULL,
+    locked_until TIMESTAMPTZ,
+    unlock_token_hash STRING UNIQUE,
+    unlock_expires_at TIMESTAMPTZ
+);
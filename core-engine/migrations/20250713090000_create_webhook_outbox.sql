@@ -0,0 +1,53 @@
+-- Tenant webhook endpoints; the circuit breaker state lives on the row
+CREATE TABLE IF NOT EXISTS webhook_endpoints (
+    id UUID PRIMARY KEY,
+    tenant_id UUID NOT NULL,
+    url STRING NOT NULL,
+    description STRING,
+    event_types STRING[] NOT NULL DEFAULT ARRAY[],
+    secret STRING NOT NULL,
+    active BOOL NOT NULL DEFAULT true,
+    consecutive_failures INT8 NOT NULL DEFAULT 0,
+    paused_until TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    INDEX webhook_endpoints_tenant_idx (tenant_id)
+);
+
+-- Written in the same transaction as the change it describes
+CREATE TABLE IF NOT EXISTS outbox_events (
+    id UUID PRIMARY KEY,
+    tenant_id UUID NOT NULL,
+    event_type STRING NOT NULL,
+    entity_type STRING NOT NULL,
+    entity_id UUID NOT NULL,
+    entity_seq INT8 NOT NULL,
+    before_state JSONB,
+    after_state JSONB,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    dispatched_at TIMESTAMPTZ,
+    UNIQUE (entity_id, entity_seq),
+    INDEX outbox_events_undispatched_idx (created_at) WHERE dispatched_at IS NULL,
+    INDEX outbox_events_tenant_created_idx (tenant_id, created_at)
+);
+
+CREATE TABLE IF NOT EXISTS webhook_deliveries (
+    id UUID PRIMARY KEY,
+    event_id UUID NOT NULL REFERENCES outbox_events(id) ON DELETE CASCADE,
+    endpoint_id UUID NOT NULL REFERENCES webhook_endpoints(id) ON DELETE CASCADE,
+    tenant_id UUID NOT NULL,
+    event_type STRING NOT NULL,
+    entity_id UUID NOT NULL,
+    entity_seq INT8 NOT NULL,
+    status STRING NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'delivered', 'failed')),
+    attempts INT8 NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMPTZ NOT NULL,
+    last_status_code INT4,
+    last_error STRING,
+    delivered_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (event_id, endpoint_id),
+    INDEX webhook_deliveries_due_idx (next_attempt_at) WHERE status = 'pending',
+    INDEX webhook_deliveries_entity_idx (endpoint_id, entity_id, entity_seq) WHERE status = 'pending',
+    INDEX webhook_deliveries_endpoint_idx (tenant_id, endpoint_id, created_at DESC)
+);
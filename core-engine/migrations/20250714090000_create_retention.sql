@@ -0,0 +1,42 @@
+-- Per-tenant overrides of the default retention for a data category
+CREATE TABLE IF NOT EXISTS retention_policies (
+    tenant_id UUID NOT NULL,
+    category STRING NOT NULL,
+    retain_days INT8 NOT NULL CHECK (retain_days > 0),
+    action STRING NOT NULL CHECK (action IN ('delete', 'archive')),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (tenant_id, category)
+);
+
+-- The latest purge of each tenant's category, updated after every batch
+CREATE TABLE IF NOT EXISTS retention_purge_progress (
+    tenant_id UUID NOT NULL,
+    category STRING NOT NULL,
+    execution_id UUID NOT NULL,
+    status STRING NOT NULL CHECK (status IN ('running', 'completed', 'incomplete', 'failed')),
+    cutoff TIMESTAMPTZ NOT NULL,
+    batches INT8 NOT NULL DEFAULT 0,
+    removed INT8 NOT NULL DEFAULT 0,
+    updated_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (tenant_id, category)
+);
+
+-- Compliance evidence of every purge. Append-only: the application never updates or deletes
+-- these rows, and its database role needs only INSERT and SELECT here
+CREATE TABLE IF NOT EXISTS retention_purge_executions (
+    id UUID PRIMARY KEY,
+    tenant_id UUID NOT NULL,
+    category STRING NOT NULL,
+    action STRING NOT NULL,
+    retain_days INT8 NOT NULL,
+    cutoff TIMESTAMPTZ NOT NULL,
+    status STRING NOT NULL,
+    batches INT8 NOT NULL,
+    removed INT8 NOT NULL,
+    oldest_removed_at TIMESTAMPTZ,
+    newest_removed_at TIMESTAMPTZ,
+    error STRING,
+    started_at TIMESTAMPTZ NOT NULL,
+    finished_at TIMESTAMPTZ NOT NULL,
+    INDEX retention_purge_executions_tenant_idx (tenant_id, started_at)
+);
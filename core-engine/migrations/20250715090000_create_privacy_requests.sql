@@ -0,0 +1,26 @@
+-- Subject access (export) and erasure requests; both wait for an admin's approval
+CREATE TABLE IF NOT EXISTS privacy_requests (
+    id UUID PRIMARY KEY,
+    kind STRING NOT NULL CHECK (kind IN ('export', 'erasure')),
+    subject_id UUID NOT NULL REFERENCES users(id),
+    requested_by UUID NOT NULL REFERENCES users(id),
+    reason STRING,
+    status STRING NOT NULL DEFAULT 'pending_approval'
+        CHECK (status IN ('pending_approval', 'approved', 'running', 'completed', 'rejected', 'blocked', 'failed')),
+    reviewed_by UUID REFERENCES users(id),
+    reviewed_at TIMESTAMPTZ,
+    review_note STRING,
+    object_key STRING,
+    -- Row counts of an export, or the verification report of an erasure
+    report JSONB,
+    error STRING,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    started_at TIMESTAMPTZ,
+    completed_at TIMESTAMPTZ,
+    INDEX privacy_requests_status_idx (status, reviewed_at),
+    INDEX privacy_requests_subject_idx (subject_id, kind)
+);
+
+-- At most one open request of each kind per user
+CREATE UNIQUE INDEX IF NOT EXISTS privacy_requests_open_idx ON privacy_requests (subject_id, kind)
+    WHERE status IN ('pending_approval', 'approved', 'running');
@@ -0,0 +1,57 @@
+-- Monthly budgets; scope, thresholds and recipients are small JSON documents
+CREATE TABLE IF NOT EXISTS budgets (
+    id UUID PRIMARY KEY,
+    tenant_id UUID NOT NULL REFERENCES users(id),
+    name STRING NOT NULL,
+    scope JSONB NOT NULL,
+    monthly_amount FLOAT8 NOT NULL CHECK (monthly_amount > 0),
+    currency STRING NOT NULL,
+    thresholds JSONB NOT NULL,
+    recipients JSONB NOT NULL DEFAULT '[]',
+    enforcement STRING,
+    created_at TIMESTAMPTZ NOT NULL,
+    INDEX budgets_tenant_idx (tenant_id, created_at)
+);
+
+-- Each threshold alerts once per budget per month
+CREATE TABLE IF NOT EXISTS budget_alerts (
+    budget_id UUID NOT NULL REFERENCES budgets(id) ON DELETE CASCADE,
+    tenant_id UUID NOT NULL REFERENCES users(id),
+    period_start DATE NOT NULL,
+    kind STRING NOT NULL,
+    threshold INT4 NOT NULL,
+    spent FLOAT8 NOT NULL,
+    projected FLOAT8 NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (budget_id, period_start, kind, threshold)
+);
+
+-- The enforcement log; at most one row per budget per month, kept after overrides and
+-- after the budget itself is deleted
+CREATE TABLE IF NOT EXISTS budget_enforcements (
+    id UUID PRIMARY KEY,
+    budget_id UUID NOT NULL,
+    tenant_id UUID NOT NULL REFERENCES users(id),
+    period_start DATE NOT NULL,
+    kind STRING NOT NULL,
+    status STRING NOT NULL,
+    spent FLOAT8 NOT NULL,
+    state JSONB NOT NULL,
+    error STRING,
+    applied_at TIMESTAMPTZ NOT NULL,
+    overridden_by UUID REFERENCES users(id),
+    overridden_at TIMESTAMPTZ,
+    override_reason STRING,
+    UNIQUE (budget_id, period_start),
+    INDEX budget_enforcements_tenant_idx (tenant_id, applied_at DESC)
+);
+
+-- Checked by the quota layer before a resource is created
+CREATE TABLE IF NOT EXISTS resource_creation_blocks (
+    id UUID PRIMARY KEY,
+    tenant_id UUID NOT NULL REFERENCES users(id),
+    scope JSONB NOT NULL,
+    reason STRING NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    INDEX resource_creation_blocks_tenant_idx (tenant_id, created_at)
+);
@@ -0,0 +1,48 @@
+-- The current resources of each provider account, replaced by the account's latest discovery run
+CREATE TABLE IF NOT EXISTS inventory_resources (
+    id UUID NOT NULL UNIQUE,
+    tenant_id UUID NOT NULL REFERENCES users(id),
+    provider STRING NOT NULL,
+    account_id STRING NOT NULL,
+    resource_key STRING NOT NULL,
+    region STRING NOT NULL,
+    resource_type STRING NOT NULL,
+    name STRING,
+    state STRING,
+    tags JSONB NOT NULL DEFAULT '{}',
+    attributes JSONB NOT NULL DEFAULT '{}',
+    first_seen_at TIMESTAMPTZ NOT NULL,
+    last_seen_at TIMESTAMPTZ NOT NULL,
+    last_run_id UUID NOT NULL,
+    PRIMARY KEY (tenant_id, provider, account_id, resource_key, region),
+    INDEX inventory_resources_type_idx (tenant_id, resource_type),
+    INDEX inventory_resources_region_idx (tenant_id, region),
+    INDEX inventory_resources_state_idx (tenant_id, state),
+    INDEX inventory_resources_name_idx (tenant_id, name),
+    INDEX inventory_resources_last_seen_idx (tenant_id, last_seen_at),
+    -- Tag and attribute containment (`@>`) and free-text ILIKE over names and keys
+    INVERTED INDEX inventory_resources_tags_idx (tenant_id, tags),
+    INVERTED INDEX inventory_resources_attributes_idx (tenant_id, attributes),
+    INVERTED INDEX inventory_resources_name_trgm_idx (tenant_id, name gin_trgm_ops),
+    INVERTED INDEX inventory_resources_key_trgm_idx (tenant_id, resource_key gin_trgm_ops)
+);
+
+-- The latest run applied per account, so a late-arriving older run can't roll the inventory back
+CREATE TABLE IF NOT EXISTS inventory_accounts (
+    tenant_id UUID NOT NULL REFERENCES users(id),
+    provider STRING NOT NULL,
+    account_id STRING NOT NULL,
+    run_id UUID NOT NULL,
+    completed_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (tenant_id, provider, account_id)
+);
+
+CREATE TABLE IF NOT EXISTS inventory_saved_searches (
+    id UUID PRIMARY KEY,
+    tenant_id UUID NOT NULL REFERENCES users(id),
+    name STRING NOT NULL,
+    query JSONB NOT NULL,
+    recipients JSONB NOT NULL DEFAULT '[]',
+    created_at TIMESTAMPTZ NOT NULL,
+    INDEX inventory_saved_searches_tenant_idx (tenant_id, created_at)
+);
@@ -0,0 +1,60 @@
+-- Tenant-defined compliance rules; the built-in pack lives in code
+CREATE TABLE IF NOT EXISTS compliance_rules (
+    tenant_id UUID NOT NULL REFERENCES users(id),
+    key STRING NOT NULL,
+    name STRING NOT NULL,
+    description STRING NOT NULL DEFAULT '',
+    severity STRING NOT NULL,
+    remediation STRING NOT NULL DEFAULT '',
+    resource_types JSONB NOT NULL DEFAULT '[]',
+    expression STRING NOT NULL,
+    enabled BOOL NOT NULL DEFAULT true,
+    PRIMARY KEY (tenant_id, key)
+);
+
+-- Built-in rules a tenant has switched off (or back on); absent means enabled
+CREATE TABLE IF NOT EXISTS compliance_builtin_settings (
+    tenant_id UUID NOT NULL REFERENCES users(id),
+    rule_key STRING NOT NULL,
+    enabled BOOL NOT NULL,
+    PRIMARY KEY (tenant_id, rule_key)
+);
+
+-- Findings stay after they resolve; a violation that returns opens a new one
+CREATE TABLE IF NOT EXISTS compliance_findings (
+    id UUID PRIMARY KEY,
+    tenant_id UUID NOT NULL REFERENCES users(id),
+    rule_key STRING NOT NULL,
+    severity STRING NOT NULL,
+    provider STRING NOT NULL,
+    account_id STRING NOT NULL,
+    resource_key STRING NOT NULL,
+    region STRING NOT NULL,
+    resource_type STRING NOT NULL,
+    resource_name STRING,
+    project STRING,
+    first_seen_at TIMESTAMPTZ NOT NULL,
+    last_seen_at TIMESTAMPTZ NOT NULL,
+    resolved_at TIMESTAMPTZ,
+    INDEX compliance_findings_tenant_idx (tenant_id, resolved_at, last_seen_at DESC)
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS compliance_findings_open_idx
+    ON compliance_findings (tenant_id, rule_key, provider, account_id, resource_key, region)
+    WHERE resolved_at IS NULL;
+
+CREATE TABLE IF NOT EXISTS compliance_scans (
+    id UUID PRIMARY KEY,
+    tenant_id UUID NOT NULL REFERENCES users(id),
+    started_at TIMESTAMPTZ NOT NULL,
+    completed_at TIMESTAMPTZ NOT NULL,
+    rules INT8 NOT NULL,
+    evaluation_errors INT8 NOT NULL,
+    opened INT8 NOT NULL,
+    resolved INT8 NOT NULL,
+    overall JSONB NOT NULL,
+    providers JSONB NOT NULL,
+    projects JSONB NOT NULL,
+    open_by_severity JSONB NOT NULL,
+    INDEX compliance_scans_tenant_idx (tenant_id, started_at DESC)
+);
@@ -0,0 +1,51 @@
+ALTER TABLE compliance_findings
+    ADD COLUMN IF NOT EXISTS status STRING NOT NULL DEFAULT 'open',
+    ADD COLUMN IF NOT EXISTS remediation_id UUID;
+
+UPDATE compliance_findings SET status = 'resolved' WHERE resolved_at IS NOT NULL;
+
+CREATE INDEX IF NOT EXISTS compliance_findings_status_idx
+    ON compliance_findings (tenant_id, status, last_seen_at DESC);
+
+-- At most one workflow template per rule; parameters map names to CEL bindings
+CREATE TABLE IF NOT EXISTS remediation_mappings (
+    id UUID PRIMARY KEY,
+    tenant_id UUID NOT NULL REFERENCES users(id),
+    rule_key STRING NOT NULL,
+    template_id STRING NOT NULL,
+    parameters JSONB NOT NULL DEFAULT '{}',
+    auto_remediate BOOL NOT NULL DEFAULT false,
+    max_concurrent INT8 NOT NULL,
+    created_by UUID NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    UNIQUE (tenant_id, rule_key),
+    INDEX remediation_mappings_auto_idx (auto_remediate)
+);
+
+CREATE TABLE IF NOT EXISTS remediations (
+    id UUID PRIMARY KEY,
+    tenant_id UUID NOT NULL REFERENCES users(id),
+    finding_id UUID NOT NULL,
+    mapping_id UUID NOT NULL,
+    rule_key STRING NOT NULL,
+    template_id STRING NOT NULL,
+    parameters JSONB NOT NULL,
+    trigger JSONB NOT NULL,
+    run_id STRING,
+    status STRING NOT NULL,
+    detail STRING,
+    started_at TIMESTAMPTZ NOT NULL,
+    completed_at TIMESTAMPTZ,
+    INDEX remediations_finding_idx (tenant_id, finding_id, started_at DESC),
+    INDEX remediations_running_idx (status, mapping_id)
+);
+
+CREATE TABLE IF NOT EXISTS remediation_events (
+    seq INT8 NOT NULL DEFAULT unique_rowid() PRIMARY KEY,
+    remediation_id UUID NOT NULL REFERENCES remediations(id) ON DELETE CASCADE,
+    tenant_id UUID NOT NULL REFERENCES users(id),
+    kind STRING NOT NULL,
+    detail JSONB NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    INDEX remediation_events_remediation_idx (tenant_id, remediation_id, created_at)
+);
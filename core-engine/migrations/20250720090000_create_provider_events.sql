@@ -0,0 +1,39 @@
+CREATE TABLE IF NOT EXISTS provider_event_sources (
+    id UUID PRIMARY KEY,
+    tenant_id UUID NOT NULL REFERENCES users(id),
+    description STRING,
+    token_hash STRING NOT NULL,
+    -- SNS topics whose signed notifications are accepted
+    topic_arns JSONB NOT NULL DEFAULT '[]',
+    created_at TIMESTAMPTZ NOT NULL,
+    INDEX provider_event_sources_tenant_idx (tenant_id)
+);
+
+-- Event ids already processed; AWS delivers at least once
+CREATE TABLE IF NOT EXISTS provider_events_processed (
+    tenant_id UUID NOT NULL REFERENCES users(id),
+    event_id STRING NOT NULL,
+    received_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (tenant_id, event_id)
+);
+
+-- Events with no mapping yet, kept raw so one can be written
+CREATE TABLE IF NOT EXISTS provider_events_unmapped (
+    id UUID PRIMARY KEY,
+    tenant_id UUID NOT NULL REFERENCES users(id),
+    source_id UUID NOT NULL,
+    event_id STRING NOT NULL,
+    event_type STRING NOT NULL,
+    payload JSONB NOT NULL,
+    received_at TIMESTAMPTZ NOT NULL,
+    INDEX provider_events_unmapped_type_idx (tenant_id, event_type, received_at DESC)
+);
+
+CREATE TABLE IF NOT EXISTS provider_event_unmapped_types (
+    tenant_id UUID NOT NULL REFERENCES users(id),
+    event_type STRING NOT NULL,
+    count INT8 NOT NULL,
+    first_seen_at TIMESTAMPTZ NOT NULL,
+    last_seen_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (tenant_id, event_type)
+);
@@ -0,0 +1,2 @@
+-- Monthly build-minute budget for image builds; NULL means unlimited
+ALTER TABLE tenant_quotas ADD COLUMN IF NOT EXISTS max_monthly_build_minutes INT8;
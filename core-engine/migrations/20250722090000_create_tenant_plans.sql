@@ -0,0 +1,17 @@
+-- Plan tier per tenant with per-feature overrides; tenants without a row are on the default plan
+CREATE TABLE IF NOT EXISTS tenant_plans (
+    tenant_id UUID PRIMARY KEY REFERENCES users(id),
+    tier STRING NOT NULL,
+    overrides JSONB NOT NULL DEFAULT '{}',
+    updated_by UUID,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Accounts already reporting discovery runs count against the cloud account entitlement
+INSERT INTO quota_holdings (tenant_id, quota, holder)
+SELECT DISTINCT tenant_id, 'cloud_accounts', provider || '/' || account_id FROM discovery_runs
+ON CONFLICT DO NOTHING;
+
+INSERT INTO quota_usage (tenant_id, quota, used)
+SELECT tenant_id, 'cloud_accounts', COUNT(*) FROM quota_holdings WHERE quota = 'cloud_accounts' GROUP BY tenant_id
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,39 @@
+-- Run history across every workflow of a project, reported by the executors. Each search filter
+-- has a (project_id, ...) index so searches stay bounded to one project at millions of runs.
+CREATE TABLE IF NOT EXISTS workflow_runs (
+    id UUID NOT NULL UNIQUE,
+    project_id UUID NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+    run_id STRING NOT NULL,
+    workflow_id STRING NOT NULL,
+    status STRING NOT NULL,
+    trigger_type STRING NOT NULL,
+    started_at TIMESTAMPTZ NOT NULL,
+    finished_at TIMESTAMPTZ,
+    duration_ms INT8,
+    failed_tasks STRING[] NOT NULL DEFAULT ARRAY[],
+    PRIMARY KEY (project_id, run_id),
+    INDEX workflow_runs_started_idx (project_id, started_at DESC, id DESC),
+    -- Unfinished runs sort as the shortest, matching the search's ORDER BY
+    INDEX workflow_runs_duration_idx (project_id, (COALESCE(duration_ms, -1)) DESC, id DESC),
+    INDEX workflow_runs_workflow_idx (project_id, workflow_id, started_at DESC),
+    INDEX workflow_runs_status_idx (project_id, status, started_at DESC),
+    INDEX workflow_runs_trigger_idx (project_id, trigger_type, started_at DESC),
+    -- Runs in which a given task failed (`failed_tasks @> ARRAY[...]`)
+    INVERTED INDEX workflow_runs_failed_tasks_idx (project_id, failed_tasks)
+);
+
+CREATE TABLE IF NOT EXISTS workflow_task_runs (
+    run_id UUID NOT NULL REFERENCES workflow_runs(id) ON DELETE CASCADE,
+    task_id STRING NOT NULL,
+    project_id UUID NOT NULL,
+    workflow_id STRING NOT NULL,
+    status STRING NOT NULL,
+    started_at TIMESTAMPTZ NOT NULL,
+    finished_at TIMESTAMPTZ,
+    error_code STRING,
+    error_message STRING,
+    PRIMARY KEY (run_id, task_id),
+    -- Failure analytics scan only failed task runs in the window
+    INDEX workflow_task_runs_failures_idx (project_id, started_at) STORING (workflow_id, error_code)
+        WHERE status IN ('failed', 'timed_out')
+);
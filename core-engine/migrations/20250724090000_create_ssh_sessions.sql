@@ -0,0 +1,25 @@
+-- SSH sessions brokered to fleet instances. Recorded sessions keep the location and chain head
+-- of their recording here, so the stored segments can be verified against it.
+CREATE TABLE IF NOT EXISTS ssh_sessions (
+    id UUID PRIMARY KEY,
+    tenant_id UUID NOT NULL REFERENCES users(id),
+    user_id UUID NOT NULL,
+    fleet_id STRING NOT NULL,
+    group_id STRING NOT NULL,
+    instance_id STRING NOT NULL,
+    host STRING NOT NULL,
+    login STRING NOT NULL,
+    mode STRING NOT NULL,
+    status STRING NOT NULL,
+    command STRING,
+    certificate_serial STRING,
+    disclosure STRING,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at TIMESTAMPTZ NOT NULL,
+    attached_at TIMESTAMPTZ,
+    ended_at TIMESTAMPTZ,
+    terminated_by UUID,
+    recording JSONB,
+    INDEX ssh_sessions_tenant_idx (tenant_id, created_at DESC),
+    INDEX ssh_sessions_status_idx (status, created_at DESC)
+);
@@ -0,0 +1,59 @@
+-- Change tracking for the exported datasets: every write bumps `updated_at`, and exports read
+-- rows in (updated_at, key) order from where the previous run stopped. Existing rows all get
+-- the migration's timestamp and ship with the first run.
+ALTER TABLE inventory_resources ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ NOT NULL DEFAULT now() ON UPDATE now();
+CREATE INDEX IF NOT EXISTS inventory_resources_updated_idx ON inventory_resources (tenant_id, updated_at);
+
+ALTER TABLE usage_daily ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ NOT NULL DEFAULT now() ON UPDATE now();
+CREATE INDEX IF NOT EXISTS usage_daily_updated_idx ON usage_daily (tenant_id, updated_at);
+
+ALTER TABLE workflow_runs ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ NOT NULL DEFAULT now() ON UPDATE now();
+CREATE INDEX IF NOT EXISTS workflow_runs_updated_idx ON workflow_runs (project_id, updated_at);
+
+-- Customer warehouses; credentials in `config` are key-vault references only
+CREATE TABLE IF NOT EXISTS warehouse_destinations (
+    id UUID PRIMARY KEY,
+    tenant_id UUID NOT NULL REFERENCES users(id),
+    name STRING NOT NULL,
+    config JSONB NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    INDEX warehouse_destinations_tenant_idx (tenant_id, created_at)
+);
+
+CREATE TABLE IF NOT EXISTS warehouse_exports (
+    id UUID PRIMARY KEY,
+    tenant_id UUID NOT NULL REFERENCES users(id),
+    destination_id UUID NOT NULL REFERENCES warehouse_destinations(id),
+    dataset STRING NOT NULL,
+    table_name STRING NOT NULL,
+    interval_minutes INT8 NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    -- The schedule slot last claimed by a scheduler, so each slot runs once
+    last_scheduled_for TIMESTAMPTZ,
+    -- Where the last successful load stopped, and the schema it shipped with
+    watermark JSONB NOT NULL,
+    shipped_schema JSONB,
+    blocked_changes JSONB NOT NULL DEFAULT '[]',
+    -- Held by the running export so manual and scheduled runs never overlap
+    lease_until TIMESTAMPTZ,
+    UNIQUE (destination_id, table_name),
+    INDEX warehouse_exports_tenant_idx (tenant_id, created_at)
+);
+
+CREATE TABLE IF NOT EXISTS warehouse_export_runs (
+    id UUID PRIMARY KEY,
+    export_id UUID NOT NULL REFERENCES warehouse_exports(id) ON DELETE CASCADE,
+    tenant_id UUID NOT NULL REFERENCES users(id),
+    trigger STRING NOT NULL,
+    status STRING NOT NULL,
+    rows INT8 NOT NULL DEFAULT 0,
+    bytes INT8 NOT NULL DEFAULT 0,
+    file STRING,
+    watermark_from JSONB NOT NULL,
+    watermark_to JSONB,
+    schema_changes JSONB NOT NULL DEFAULT '[]',
+    error STRING,
+    started_at TIMESTAMPTZ NOT NULL,
+    finished_at TIMESTAMPTZ,
+    INDEX warehouse_export_runs_export_idx (tenant_id, export_id, started_at DESC)
+);
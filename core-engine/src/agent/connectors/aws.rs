@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 use aws_config::{BehaviorVersion, Region};
-use aws_sdk_ec2::{Client as Ec2Client, types::Instance};
+use aws_sdk_ec2::{Client as Ec2Client, types::{Instance, Subnet, Tag, Vpc}};
 use aws_sdk_s3::{Client as S3Client, types::Bucket};
 use serde::{Deserialize, Serialize};
 
@@ -98,6 +98,18 @@ impl AwsAgent {
                         Err(e) => errors.push(format!("S3 discovery failed: {}", e)),
                     }
                 }
+                "vpc" => {
+                    match self.discover_vpcs().await {
+                        Ok(mut vpc_resources) => resources.append(&mut vpc_resources),
+                        Err(e) => errors.push(format!("VPC discovery failed: {}", e)),
+                    }
+                }
+                "subnet" => {
+                    match self.discover_subnets().await {
+                        Ok(mut subnet_resources) => resources.append(&mut subnet_resources),
+                        Err(e) => errors.push(format!("Subnet discovery failed: {}", e)),
+                    }
+                }
                 _ => {
                     errors.push(format!("Unsupported resource type: {}", resource_type));
                 }
@@ -156,6 +168,74 @@ impl AwsAgent {
         Ok(resources)
     }
 
+    async fn discover_vpcs(&self) -> AppResult<Vec<AwsResource>> {
+        let ec2_client = self.ec2_client.as_ref()
+            .ok_or_else(|| AppError::Configuration("EC2 client not initialized".into()))?;
+
+        let resp = ec2_client
+            .describe_vpcs()
+            .send()
+            .await
+            .map_err(|e| AppError::ExternalService(format!("EC2 API error: {}", e)))?;
+
+        Ok(resp.vpcs().iter().map(|vpc| self.vpc_to_resource(vpc)).collect())
+    }
+
+    async fn discover_subnets(&self) -> AppResult<Vec<AwsResource>> {
+        let ec2_client = self.ec2_client.as_ref()
+            .ok_or_else(|| AppError::Configuration("EC2 client not initialized".into()))?;
+
+        let resp = ec2_client
+            .describe_subnets()
+            .send()
+            .await
+            .map_err(|e| AppError::ExternalService(format!("EC2 API error: {}", e)))?;
+
+        Ok(resp.subnets().iter().map(|subnet| self.subnet_to_resource(subnet)).collect())
+    }
+
+    // `cidr_block` is what IPAM checks new allocations against
+    fn vpc_to_resource(&self, vpc: &Vpc) -> AwsResource {
+        let tags = tags_to_map(vpc.tags());
+        let mut metadata = HashMap::new();
+        metadata.insert("cidr_block".to_string(), vpc.cidr_block().unwrap_or_default().to_string());
+        metadata.insert("owner_id".to_string(), vpc.owner_id().unwrap_or_default().to_string());
+        metadata.insert("state".to_string(),
+            vpc.state().map(|s| s.as_str().to_string()).unwrap_or_default());
+
+        let vpc_id = vpc.vpc_id().unwrap_or_default();
+        AwsResource {
+            resource_type: "ec2:vpc".to_string(),
+            resource_id: vpc_id.to_string(),
+            name: tags.get("Name").cloned(),
+            arn: Some(format!("arn:aws:ec2:{}:{}:vpc/{}", self.config.region, vpc.owner_id().unwrap_or_default(), vpc_id)),
+            region: self.config.region.clone(),
+            tags,
+            metadata,
+            cost_estimate: None,
+        }
+    }
+
+    fn subnet_to_resource(&self, subnet: &Subnet) -> AwsResource {
+        let tags = tags_to_map(subnet.tags());
+        let mut metadata = HashMap::new();
+        metadata.insert("cidr_block".to_string(), subnet.cidr_block().unwrap_or_default().to_string());
+        metadata.insert("vpc_id".to_string(), subnet.vpc_id().unwrap_or_default().to_string());
+        metadata.insert("owner_id".to_string(), subnet.owner_id().unwrap_or_default().to_string());
+        metadata.insert("availability_zone".to_string(), subnet.availability_zone().unwrap_or_default().to_string());
+
+        AwsResource {
+            resource_type: "ec2:subnet".to_string(),
+            resource_id: subnet.subnet_id().unwrap_or_default().to_string(),
+            name: tags.get("Name").cloned(),
+            arn: subnet.subnet_arn().map(str::to_string),
+            region: self.config.region.clone(),
+            tags,
+            metadata,
+            cost_estimate: None,
+        }
+    }
+
     fn instance_to_resource(&self, instance: &Instance) -> AwsResource {
         let mut tags = HashMap::new();
         let mut metadata = HashMap::new();
@@ -352,6 +432,12 @@ impl AwsAgent {
     }
 }
 
+fn tags_to_map(tags: &[Tag]) -> HashMap<String, String> {
+    tags.iter()
+        .filter_map(|tag| Some((tag.key()?.to_string(), tag.value()?.to_string())))
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -8,6 +8,7 @@ pub use gcp::{GcpAgent, GcpConfig, GcpDiscoveryResult};
 
 use std::collections::HashMap;
 use serde::{Deserialize, Serialize};
+use crate::discovery::DiscoveredResource;
 use crate::error::{AppError, AppResult};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -39,6 +40,21 @@ pub struct CloudResource {
     pub cost_estimate: Option<f64>,
 }
 
+impl From<&AwsResource> for DiscoveredResource {
+    fn from(resource: &AwsResource) -> Self {
+        let mut normalized =
+            DiscoveredResource::new(&resource.resource_type, &resource.resource_id, resource.arn.as_deref(), &resource.region)
+                .with_name(resource.name.clone())
+                .with_tags(resource.tags.clone())
+                .with_attribute("resource_id", resource.resource_id.clone());
+        normalized.attributes.extend(resource.metadata.iter().map(|(k, v)| (k.clone(), v.clone().into())));
+        if let Some(cost) = resource.cost_estimate {
+            normalized = normalized.with_attribute("cost_estimate", cost);
+        }
+        normalized
+    }
+}
+
 pub struct ConnectorManager {
     aws_agents: HashMap<String, AwsAgent>,
     azure_agents: HashMap<String, AzureAgent>,
@@ -1,9 +1,12 @@
 use std::collections::HashMap;
+use axum::async_trait;
 use redis::AsyncCommands;
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 use uuid::Uuid;
 
 use crate::error::{AppError, AppResult};
+use crate::privacy::{Subject, SubjectTable};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AgentContext {
@@ -267,6 +270,62 @@ impl ContextStore {
             .map_err(|e| AppError::Connection(format!("Failed to get agent keys: {}", e)))?;
         Ok(keys.len())
     }
+
+    // Sessions aren't indexed by user, so this walks them all; they expire within a day anyway
+    pub async fn sessions_for_user(&self, user_id: &str) -> AppResult<Vec<SessionContext>> {
+        let mut conn = self.get_connection().await?;
+        let keys: Vec<String> = conn.keys("session:*").await
+            .map_err(|e| AppError::Connection(format!("Failed to get session keys: {}", e)))?;
+        let mut sessions = Vec::new();
+        for key in keys {
+            let Some(session_id) = key.strip_prefix("session:") else { continue };
+            match self.get_session_context(session_id).await {
+                Ok(session) if session.user_id == user_id => sessions.push(session),
+                Ok(_) | Err(AppError::NotFound(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(sessions)
+    }
+}
+
+// Agent sessions and their conversations, for privacy export and erasure
+#[async_trait]
+impl SubjectTable for ContextStore {
+    fn name(&self) -> &'static str {
+        "agent_sessions"
+    }
+
+    async fn collect(&self, subject: &Subject) -> AppResult<Vec<Value>> {
+        let mut rows = Vec::new();
+        for session in self.sessions_for_user(&subject.id.to_string()).await? {
+            let mut agents = Vec::new();
+            for agent_id in &session.agents {
+                match self.get_agent_context(agent_id).await {
+                    Ok(agent) => agents.push(agent),
+                    Err(AppError::NotFound(_)) => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            rows.push(json!({ "session": session, "agents": agents }));
+        }
+        Ok(rows)
+    }
+
+    async fn delete(&self, subject: &Subject) -> AppResult<u64> {
+        let sessions = self.sessions_for_user(&subject.id.to_string()).await?;
+        for session in &sessions {
+            for agent_id in &session.agents {
+                self.remove_agent(agent_id).await?;
+            }
+            self.remove_session(&session.session_id).await?;
+        }
+        Ok(sessions.len() as u64)
+    }
+
+    async fn remaining(&self, subject: &Subject) -> AppResult<u64> {
+        Ok(self.sessions_for_user(&subject.id.to_string()).await?.len() as u64)
+    }
 }
 
 #[cfg(test)]
@@ -1,7 +1,11 @@
 pub mod connectors;
 pub mod context;
 pub mod manager;
+pub mod pricing;
 pub mod service;
+pub mod sizing;
 
 pub use manager::AgentManager;
+pub use pricing::{CostComparison, CostEstimator, PlanItem};
 pub use service::AgentService;
+pub use sizing::{RightSizer, SizingPlan};
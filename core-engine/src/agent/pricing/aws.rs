@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+
+use super::{round_cents, tiered_cost, LinePrice, PriceSource, HOURS_PER_MONTH};
+use crate::discovery::DiscoveredResource;
+use crate::error::AppResult;
+
+// On-demand Linux list prices in USD
+const US_EAST_1_INSTANCES: &[(&str, f64)] = &[
+    ("t3.micro", 0.0104),
+    ("t3.small", 0.0208),
+    ("t3.medium", 0.0416),
+    ("t3.large", 0.0832),
+    ("m5.large", 0.096),
+    ("m5.xlarge", 0.192),
+    ("m5.2xlarge", 0.384),
+    ("m5d.large", 0.113),
+    ("m5d.xlarge", 0.226),
+    ("m6g.large", 0.077),
+    ("m6g.xlarge", 0.154),
+    ("c5.large", 0.085),
+    ("c5.xlarge", 0.17),
+    ("r5.large", 0.126),
+    ("r5.xlarge", 0.252),
+    ("db.t3.micro", 0.017),
+    ("db.t3.small", 0.034),
+    ("db.m5.large", 0.171),
+    ("db.r5.large", 0.25),
+];
+
+const US_EAST_1_VOLUMES: &[(&str, f64)] = &[
+    ("gp3", 0.08),
+    ("gp2", 0.10),
+    ("io1", 0.125),
+    ("st1", 0.045),
+    ("sc1", 0.015),
+];
+
+// (tier start in GB, price per GB); the first 100 GB a month are free
+const INTERNET_EGRESS_TIERS: &[(f64, f64)] = &[
+    (0.0, 0.0),
+    (100.0, 0.09),
+    (10_340.0, 0.085),
+    (51_300.0, 0.07),
+    (153_700.0, 0.05),
+];
+
+// Static AWS list prices for the resources we currently migrate away from. Regions and
+// types without a rate come back unpriced.
+pub struct AwsPriceTable {
+    hourly: HashMap<(String, String), f64>,
+    per_gb_month: HashMap<(String, String), f64>,
+}
+
+impl Default for AwsPriceTable {
+    fn default() -> Self {
+        let mut table = Self { hourly: HashMap::new(), per_gb_month: HashMap::new() };
+        for (instance_type, rate) in US_EAST_1_INSTANCES {
+            table = table.with_hourly_rate("us-east-1", instance_type, *rate);
+        }
+        for (volume_type, rate) in US_EAST_1_VOLUMES {
+            table = table.with_volume_rate("us-east-1", volume_type, *rate);
+        }
+        table
+    }
+}
+
+impl AwsPriceTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_hourly_rate(mut self, region: &str, instance_type: &str, rate: f64) -> Self {
+        self.hourly.insert((region.to_string(), instance_type.to_string()), rate);
+        self
+    }
+
+    pub fn with_volume_rate(mut self, region: &str, volume_type: &str, rate: f64) -> Self {
+        self.per_gb_month.insert((region.to_string(), volume_type.to_string()), rate);
+        self
+    }
+
+    fn instance_price(&self, resource: &DiscoveredResource, attribute: &str) -> LinePrice {
+        let Some(instance_type) = resource.attributes.get(attribute).and_then(|v| v.as_str()) else {
+            return LinePrice::unpriced(format!("missing {}", attribute));
+        };
+        match self.hourly.get(&(resource.region.clone(), instance_type.to_string())) {
+            Some(rate) => LinePrice::Priced {
+                meter: instance_type.to_string(),
+                unit: "1 Hour".to_string(),
+                unit_price: *rate,
+                quantity: HOURS_PER_MONTH,
+                monthly_cost: round_cents(rate * HOURS_PER_MONTH),
+            },
+            None => LinePrice::unpriced(format!("no rate for {} in {}", instance_type, resource.region)),
+        }
+    }
+
+    fn volume_price(&self, resource: &DiscoveredResource) -> LinePrice {
+        let volume_type = resource.attributes.get("volume_type").and_then(|v| v.as_str()).unwrap_or("gp2");
+        let Some(size_gb) = resource.attributes.get("size_gb").and_then(|v| v.as_f64()) else {
+            return LinePrice::unpriced("missing size_gb");
+        };
+        match self.per_gb_month.get(&(resource.region.clone(), volume_type.to_string())) {
+            Some(rate) => LinePrice::Priced {
+                meter: format!("EBS {}", volume_type),
+                unit: "1 GB/Month".to_string(),
+                unit_price: *rate,
+                quantity: size_gb,
+                monthly_cost: round_cents(rate * size_gb),
+            },
+            None => LinePrice::unpriced(format!("no rate for {} volumes in {}", volume_type, resource.region)),
+        }
+    }
+}
+
+#[async_trait]
+impl PriceSource for AwsPriceTable {
+    fn provider(&self) -> &str {
+        "aws"
+    }
+
+    fn currency(&self) -> &str {
+        "USD"
+    }
+
+    async fn price(&self, resource: &DiscoveredResource, _now: DateTime<Utc>) -> AppResult<LinePrice> {
+        Ok(match resource.resource_type.as_str() {
+            "ec2:instance" => self.instance_price(resource, "instance_type"),
+            "rds:instance" => self.instance_price(resource, "instance_class"),
+            "ebs:volume" => self.volume_price(resource),
+            "egress" => match resource.attributes.get("gb_per_month").and_then(|v| v.as_f64()) {
+                Some(gb) => LinePrice::Priced {
+                    meter: "Data Transfer Out to Internet".to_string(),
+                    unit: "1 GB".to_string(),
+                    unit_price: INTERNET_EGRESS_TIERS[1].1,
+                    quantity: gb,
+                    monthly_cost: round_cents(tiered_cost(INTERNET_EGRESS_TIERS, gb)),
+                },
+                None => LinePrice::unpriced("missing gb_per_month"),
+            },
+            other => LinePrice::unpriced(format!("no AWS pricing for {}", other)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_unknown_instance_types_are_unpriced() {
+        let table = AwsPriceTable::new();
+        let now = Utc::now();
+        let known = DiscoveredResource::new("ec2:instance", "i-1", None, "us-east-1").with_attribute("instance_type", "m5.large");
+        let unknown = DiscoveredResource::new("ec2:instance", "i-2", None, "us-east-1").with_attribute("instance_type", "x9.huge");
+
+        assert_eq!(table.price(&known, now).await.unwrap().monthly_cost(), Some(70.08));
+        assert!(matches!(table.price(&unknown, now).await.unwrap(), LinePrice::Unpriced { .. }));
+        assert!((tiered_cost(INTERNET_EGRESS_TIERS, 600.0) - 45.0).abs() < 1e-9);
+    }
+}
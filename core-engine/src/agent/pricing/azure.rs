@@ -0,0 +1,506 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use axum::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use reqwest::Client as HttpClient;
+use serde::{Deserialize, Serialize};
+
+use super::{round_cents, tiered_cost, LinePrice, PriceSource, HOURS_PER_MONTH};
+use crate::discovery::DiscoveredResource;
+use crate::error::{AppError, AppResult};
+
+const RETAIL_PRICES_URL: &str = "https://prices.azure.com/api/retail/prices";
+const DEFAULT_CACHE_TTL_HOURS: i64 = 24;
+// The API pages at 100 items; no single SKU query we make comes close to this
+const MAX_PAGES: usize = 20;
+// Internet egress; inter-region and inter-zone transfer are separate meters
+const EGRESS_METER: &str = "Standard Data Transfer Out";
+
+// Managed disk tiers by provisioned size in GiB; Standard HDD starts at S4
+const DISK_TIERS: &[(f64, u32)] = &[
+    (4.0, 1),
+    (8.0, 2),
+    (16.0, 3),
+    (32.0, 4),
+    (64.0, 6),
+    (128.0, 10),
+    (256.0, 15),
+    (512.0, 20),
+    (1024.0, 30),
+    (2048.0, 40),
+    (4096.0, 50),
+    (8192.0, 60),
+    (16384.0, 70),
+    (32767.0, 80),
+];
+
+// One price meter as returned by the Retail Prices API
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetailPrice {
+    pub currency_code: String,
+    #[serde(default)]
+    pub tier_minimum_units: f64,
+    pub retail_price: f64,
+    pub arm_region_name: String,
+    pub meter_name: String,
+    pub product_name: String,
+    pub sku_name: String,
+    #[serde(default)]
+    pub arm_sku_name: String,
+    pub service_name: String,
+    pub unit_of_measure: String,
+    #[serde(rename = "type")]
+    pub price_type: String,
+    #[serde(default)]
+    pub is_primary_meter_region: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct RetailPricesPage {
+    items: Vec<RetailPrice>,
+    next_page_link: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedPrices {
+    fetched_at: DateTime<Utc>,
+    items: Vec<RetailPrice>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PriceQuery {
+    pub service_name: String,
+    pub region: String,
+    // `armSkuName` for VM sizes, `skuName` for everything else
+    pub sku_field: &'static str,
+    pub sku: String,
+}
+
+impl PriceQuery {
+    pub fn new(service_name: &str, region: &str, sku_field: &'static str, sku: &str) -> Self {
+        Self {
+            service_name: service_name.to_string(),
+            region: region.to_string(),
+            sku_field,
+            sku: sku.to_string(),
+        }
+    }
+
+    fn filter(&self) -> String {
+        format!(
+            "serviceName eq '{}' and armRegionName eq '{}' and {} eq '{}' and priceType eq 'Consumption'",
+            self.service_name.replace('\'', "''"),
+            self.region.replace('\'', "''"),
+            self.sku_field,
+            self.sku.replace('\'', "''"),
+        )
+    }
+
+    fn cache_key(&self, currency: &str) -> String {
+        format!("{}-{}-{}-{}", currency, self.service_name, self.region, self.sku)
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c.to_ascii_lowercase() } else { '_' })
+            .collect()
+    }
+}
+
+// Azure list prices from the public Retail Prices API. Responses are cached in memory and,
+// with a cache directory, on disk so repeated assessments don't refetch the same meters.
+pub struct AzureRetailPrices {
+    http: HttpClient,
+    base_url: String,
+    currency: String,
+    cache_ttl: Duration,
+    cache_dir: Option<PathBuf>,
+    cache: Mutex<HashMap<String, CachedPrices>>,
+}
+
+impl Default for AzureRetailPrices {
+    fn default() -> Self {
+        Self {
+            http: HttpClient::new(),
+            base_url: RETAIL_PRICES_URL.to_string(),
+            currency: "USD".to_string(),
+            cache_ttl: Duration::hours(DEFAULT_CACHE_TTL_HOURS),
+            cache_dir: None,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl AzureRetailPrices {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_currency(mut self, currency: &str) -> Self {
+        self.currency = currency.to_ascii_uppercase();
+        self
+    }
+
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    pub fn with_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.cache_ttl = ttl;
+        self
+    }
+
+    pub fn with_cache_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.cache_dir = Some(dir.into());
+        self
+    }
+
+    fn is_fresh(&self, cached: &CachedPrices, now: DateTime<Utc>) -> bool {
+        now - cached.fetched_at < self.cache_ttl
+    }
+
+    async fn read_cache_file(&self, key: &str, now: DateTime<Utc>) -> Option<CachedPrices> {
+        let path = self.cache_dir.as_ref()?.join(format!("{}.json", key));
+        let body = tokio::fs::read(&path).await.ok()?;
+        match serde_json::from_slice::<CachedPrices>(&body) {
+            Ok(cached) if self.is_fresh(&cached, now) => Some(cached),
+            Ok(_) => None,
+            Err(e) => {
+                tracing::warn!("Ignoring unreadable price cache {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    async fn write_cache_file(&self, key: &str, cached: &CachedPrices) {
+        let Some(dir) = &self.cache_dir else { return };
+        let result = async {
+            tokio::fs::create_dir_all(dir).await?;
+            let body = serde_json::to_vec_pretty(cached).map_err(std::io::Error::other)?;
+            tokio::fs::write(dir.join(format!("{}.json", key)), body).await
+        }
+        .await;
+        if let Err(e) = result {
+            tracing::warn!("Failed to write price cache {}: {}", key, e);
+        }
+    }
+
+    async fn fetch(&self, query: &PriceQuery) -> AppResult<Vec<RetailPrice>> {
+        let mut items = Vec::new();
+        let mut request = self.http.get(&self.base_url).query(&[
+            ("currencyCode", format!("'{}'", self.currency)),
+            ("$filter", query.filter()),
+        ]);
+        for _ in 0..MAX_PAGES {
+            let page: RetailPricesPage = request
+                .send()
+                .await
+                .and_then(|r| r.error_for_status())
+                .map_err(|e| AppError::ExternalService(format!("Azure Retail Prices request failed: {}", e)))?
+                .json()
+                .await
+                .map_err(|e| AppError::ExternalService(format!("Invalid Azure Retail Prices response: {}", e)))?;
+            items.extend(page.items);
+            match page.next_page_link {
+                Some(next) => request = self.http.get(next),
+                None => return Ok(items),
+            }
+        }
+        Err(AppError::ExternalService(format!(
+            "Azure Retail Prices returned more than {} pages for {}",
+            MAX_PAGES,
+            query.filter()
+        )))
+    }
+
+    pub async fn prices(&self, query: &PriceQuery, now: DateTime<Utc>) -> AppResult<Vec<RetailPrice>> {
+        let key = query.cache_key(&self.currency);
+        if let Some(cached) = self.cache.lock().unwrap().get(&key) {
+            if self.is_fresh(cached, now) {
+                return Ok(cached.items.clone());
+            }
+        }
+
+        let cached = match self.read_cache_file(&key, now).await {
+            Some(cached) => cached,
+            None => {
+                let cached = CachedPrices { fetched_at: now, items: self.fetch(query).await? };
+                self.write_cache_file(&key, &cached).await;
+                cached
+            }
+        };
+        let items = cached.items.clone();
+        self.cache.lock().unwrap().insert(key, cached);
+        Ok(items)
+    }
+
+    async fn vm_price(&self, resource: &DiscoveredResource, now: DateTime<Utc>) -> AppResult<LinePrice> {
+        let Some(size) = attribute_str(resource, "vm_size") else {
+            return Ok(LinePrice::unpriced("missing vm_size"));
+        };
+        let windows = attribute_str(resource, "os_type").is_some_and(|os| os.eq_ignore_ascii_case("windows"));
+        let items = self.prices(&PriceQuery::new("Virtual Machines", &resource.region, "armSkuName", size), now).await?;
+        let mut candidates: Vec<&RetailPrice> = items
+            .iter()
+            .filter(|p| !p.meter_name.contains("Spot") && !p.meter_name.contains("Low Priority"))
+            .filter(|p| p.product_name.contains("Windows") == windows)
+            .collect();
+        candidates.sort_by_key(|p| !p.is_primary_meter_region);
+        Ok(priced(candidates.first().copied(), 1.0)
+            .unwrap_or_else(|| LinePrice::unpriced(format!("no price for VM size {} in {}", size, resource.region))))
+    }
+
+    async fn disk_price(&self, resource: &DiscoveredResource, now: DateTime<Utc>) -> AppResult<LinePrice> {
+        let sku = attribute_str(resource, "sku_name").unwrap_or("Premium_LRS");
+        let Some(size_gb) = resource.attributes.get("disk_size_gb").and_then(|v| v.as_f64()) else {
+            return Ok(LinePrice::unpriced("missing disk_size_gb"));
+        };
+        let Some(tier) = disk_tier(sku, size_gb) else {
+            return Ok(LinePrice::unpriced(format!("no managed disk tier for {} at {} GiB", sku, size_gb)));
+        };
+        let items = self.prices(&PriceQuery::new("Storage", &resource.region, "skuName", &tier), now).await?;
+        let meter = format!("{} Disk", tier);
+        Ok(priced(items.iter().find(|p| p.meter_name == meter), 1.0)
+            .unwrap_or_else(|| LinePrice::unpriced(format!("no price for disk {} in {}", tier, resource.region))))
+    }
+
+    async fn sql_price(&self, resource: &DiscoveredResource, now: DateTime<Utc>) -> AppResult<LinePrice> {
+        let Some(sku) = attribute_str(resource, "sku_name") else {
+            return Ok(LinePrice::unpriced("missing sku_name"));
+        };
+        let found = match parse_vcore_sku(sku) {
+            // vCore meters are priced per vCore hour
+            Some((tier, generation, vcores)) => {
+                let items = self.prices(&PriceQuery::new("SQL Database", &resource.region, "skuName", "vCore"), now).await?;
+                let product = format!("{} - Compute {}", tier, generation);
+                priced(
+                    items.iter().find(|p| p.product_name.contains("Single") && p.product_name.contains(&product)),
+                    vcores,
+                )
+            }
+            None => {
+                let items = self.prices(&PriceQuery::new("SQL Database", &resource.region, "skuName", sku), now).await?;
+                priced(items.iter().find(|p| p.product_name.starts_with("SQL Database Single")), 1.0)
+            }
+        };
+        Ok(found.unwrap_or_else(|| LinePrice::unpriced(format!("no price for SQL tier {} in {}", sku, resource.region))))
+    }
+
+    async fn egress_price(&self, resource: &DiscoveredResource, now: DateTime<Utc>) -> AppResult<LinePrice> {
+        let Some(gb) = resource.attributes.get("gb_per_month").and_then(|v| v.as_f64()) else {
+            return Ok(LinePrice::unpriced("missing gb_per_month"));
+        };
+        let items = self.prices(&PriceQuery::new("Bandwidth", &resource.region, "skuName", "Standard"), now).await?;
+        let mut tiers: Vec<(f64, f64)> = items
+            .iter()
+            .filter(|p| p.meter_name == EGRESS_METER)
+            .map(|p| (p.tier_minimum_units, p.retail_price))
+            .collect();
+        if tiers.is_empty() {
+            return Ok(LinePrice::unpriced(format!("no egress price in {}", resource.region)));
+        }
+        tiers.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let unit_price = tiers.iter().map(|t| t.1).find(|p| *p > 0.0).unwrap_or_default();
+        Ok(LinePrice::Priced {
+            meter: EGRESS_METER.to_string(),
+            unit: "1 GB".to_string(),
+            unit_price,
+            quantity: gb,
+            monthly_cost: round_cents(tiered_cost(&tiers, gb)),
+        })
+    }
+}
+
+#[async_trait]
+impl PriceSource for AzureRetailPrices {
+    fn provider(&self) -> &str {
+        "azure"
+    }
+
+    fn currency(&self) -> &str {
+        &self.currency
+    }
+
+    async fn price(&self, resource: &DiscoveredResource, now: DateTime<Utc>) -> AppResult<LinePrice> {
+        match resource.resource_type.to_ascii_lowercase().as_str() {
+            "microsoft.compute/virtualmachines" => self.vm_price(resource, now).await,
+            "microsoft.compute/disks" => self.disk_price(resource, now).await,
+            "microsoft.sql/servers/databases" => self.sql_price(resource, now).await,
+            "egress" => self.egress_price(resource, now).await,
+            other => Ok(LinePrice::unpriced(format!("no Azure pricing for {}", other))),
+        }
+    }
+}
+
+fn attribute_str<'a>(resource: &'a DiscoveredResource, key: &str) -> Option<&'a str> {
+    resource.attributes.get(key).and_then(|v| v.as_str())
+}
+
+// How many billing units of `unit_of_measure` a month holds, e.g. 730 for "1 Hour"
+fn units_per_month(unit_of_measure: &str) -> Option<f64> {
+    let unit = unit_of_measure.trim();
+    let digits: String = unit.chars().take_while(|c| c.is_ascii_digit()).collect();
+    let per: f64 = if digits.is_empty() { 1.0 } else { digits.parse().ok()? };
+    let rest = unit[digits.len()..].trim_start_matches(['/', ' ']).to_ascii_lowercase();
+    let months = match rest.as_str() {
+        "hour" | "hours" => HOURS_PER_MONTH,
+        "day" | "days" => HOURS_PER_MONTH / 24.0,
+        "month" | "months" => 1.0,
+        _ => return None,
+    };
+    Some(months / per)
+}
+
+fn priced(price: Option<&RetailPrice>, quantity: f64) -> Option<LinePrice> {
+    let price = price?;
+    let units = units_per_month(&price.unit_of_measure)? * quantity;
+    Some(LinePrice::Priced {
+        meter: format!("{} / {}", price.product_name, price.meter_name),
+        unit: price.unit_of_measure.clone(),
+        unit_price: price.retail_price,
+        quantity: units,
+        monthly_cost: round_cents(price.retail_price * units),
+    })
+}
+
+// "Premium_LRS" at 100 GiB -> "P10 LRS"
+fn disk_tier(sku: &str, size_gb: f64) -> Option<String> {
+    let (kind, redundancy) = sku.split_once('_')?;
+    let prefix = match kind {
+        "Premium" => "P",
+        "StandardSSD" => "E",
+        "Standard" => "S",
+        _ => return None,
+    };
+    let (_, tier) = DISK_TIERS
+        .iter()
+        .filter(|(_, tier)| prefix != "S" || *tier >= 4)
+        .find(|(max_gb, _)| size_gb <= *max_gb)?;
+    Some(format!("{}{} {}", prefix, tier, redundancy))
+}
+
+// "GP_Gen5_2" -> ("General Purpose", "Gen5", 2 vCores)
+fn parse_vcore_sku(sku: &str) -> Option<(&'static str, &str, f64)> {
+    let mut parts = sku.split('_');
+    let tier = match parts.next()? {
+        "GP" => "General Purpose",
+        "BC" => "Business Critical",
+        "HS" => "Hyperscale",
+        _ => return None,
+    };
+    let generation = parts.next()?;
+    let vcores = parts.next()?.parse().ok()?;
+    Some((tier, generation, vcores))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::agent::pricing::{AwsPriceTable, CostEstimator, PlanItem};
+
+    const FIXTURES: &[(&str, &str)] = &[
+        ("usd-virtual_machines-eastus-standard_d2s_v3", include_str!("fixtures/usd-virtual_machines-eastus-standard_d2s_v3.json")),
+        ("usd-virtual_machines-eastus-standard_x99", include_str!("fixtures/usd-virtual_machines-eastus-standard_x99.json")),
+        ("usd-storage-eastus-p10_lrs", include_str!("fixtures/usd-storage-eastus-p10_lrs.json")),
+        ("usd-sql_database-eastus-vcore", include_str!("fixtures/usd-sql_database-eastus-vcore.json")),
+        ("usd-bandwidth-eastus-standard", include_str!("fixtures/usd-bandwidth-eastus-standard.json")),
+        ("eur-virtual_machines-eastus-standard_d2s_v3", include_str!("fixtures/eur-virtual_machines-eastus-standard_d2s_v3.json")),
+    ];
+
+    fn fixture_cache() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("azure-prices-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (key, body) in FIXTURES {
+            std::fs::write(dir.join(format!("{}.json", key)), body).unwrap();
+        }
+        dir
+    }
+
+    // Anything not in the fixtures has to fail rather than reach the real API
+    fn offline_prices(dir: &PathBuf) -> AzureRetailPrices {
+        AzureRetailPrices::new().with_base_url("http://127.0.0.1:9/unreachable").with_cache_dir(dir)
+    }
+
+    fn fixture_time() -> DateTime<Utc> {
+        "2026-10-01T06:00:00Z".parse().unwrap()
+    }
+
+    fn aws(kind: &str, id: &str, key: &str, value: impl Into<serde_json::Value>) -> Option<DiscoveredResource> {
+        Some(DiscoveredResource::new(kind, id, None, "us-east-1").with_attribute(key, value))
+    }
+
+    fn azure(kind: &str, id: &str, key: &str, value: impl Into<serde_json::Value>) -> DiscoveredResource {
+        DiscoveredResource::new(kind, id, None, "eastus").with_attribute(key, value)
+    }
+
+    #[tokio::test]
+    async fn test_side_by_side_delta_reports_unknown_skus_as_unpriced() {
+        let dir = fixture_cache();
+        let estimator = CostEstimator::new(Arc::new(AwsPriceTable::new()), Arc::new(offline_prices(&dir)));
+        let plan = vec![
+            PlanItem {
+                key: "web".into(),
+                current: aws("ec2:instance", "i-web", "instance_type", "m5.large"),
+                target: Some(azure("microsoft.compute/virtualmachines", "web", "vm_size", "Standard_D2s_v3")),
+            },
+            PlanItem {
+                key: "web-disk".into(),
+                current: Some(
+                    DiscoveredResource::new("ebs:volume", "vol-1", None, "us-east-1")
+                        .with_attribute("volume_type", "gp3")
+                        .with_attribute("size_gb", 128),
+                ),
+                target: Some(
+                    azure("microsoft.compute/disks", "web-disk", "sku_name", "Premium_LRS").with_attribute("disk_size_gb", 100),
+                ),
+            },
+            PlanItem {
+                key: "db".into(),
+                current: aws("rds:instance", "db-1", "instance_class", "db.m5.large"),
+                target: Some(azure("microsoft.sql/servers/databases", "db", "sku_name", "GP_Gen5_2")),
+            },
+            PlanItem {
+                key: "egress".into(),
+                current: aws("egress", "internet", "gb_per_month", 600),
+                target: Some(azure("egress", "internet", "gb_per_month", 600)),
+            },
+            PlanItem {
+                key: "batch".into(),
+                current: aws("ec2:instance", "i-batch", "instance_type", "t3.large"),
+                target: Some(azure("microsoft.compute/virtualmachines", "batch", "vm_size", "Standard_X99")),
+            },
+        ];
+
+        let comparison = estimator.compare(&plan, fixture_time()).await.unwrap();
+        let deltas: Vec<Option<f64>> = comparison.lines.iter().map(|l| l.delta).collect();
+        assert_eq!(deltas, vec![Some(0.0), Some(9.47), Some(243.38), Some(-1.5), None]);
+        assert_eq!(comparison.monthly_delta, 251.35);
+        assert_eq!(comparison.current.monthly_total, 310.89);
+        assert_eq!(comparison.target.monthly_total, 501.5);
+        assert_eq!(comparison.target.unpriced, vec!["microsoft.compute/virtualmachines/batch".to_string()]);
+        assert_eq!(comparison.incomplete, vec!["batch".to_string()]);
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_currency_selection_and_stale_cache() {
+        let dir = fixture_cache();
+        let vm = azure("microsoft.compute/virtualmachines", "web", "vm_size", "Standard_D2s_v3");
+
+        let eur = offline_prices(&dir).with_currency("eur");
+        assert_eq!(eur.price(&vm, fixture_time()).await.unwrap().monthly_cost(), Some(64.9));
+        let mixed = CostEstimator::new(Arc::new(AwsPriceTable::new()), Arc::new(eur));
+        assert!(matches!(mixed.compare(&[], fixture_time()).await, Err(AppError::InvalidInput(_))));
+
+        // Once the cached response is older than the TTL it has to be refetched
+        let usd = offline_prices(&dir);
+        assert!(usd.price(&vm, fixture_time()).await.is_ok());
+        let stale = fixture_time() + Duration::days(2);
+        assert!(matches!(usd.price(&vm, stale).await, Err(AppError::ExternalService(_))));
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
@@ -0,0 +1,218 @@
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::discovery::DiscoveredResource;
+use crate::error::{AppError, AppResult};
+
+pub mod aws;
+pub mod azure;
+
+pub use aws::AwsPriceTable;
+pub use azure::AzureRetailPrices;
+
+// Both providers bill a month as 730 hours
+pub const HOURS_PER_MONTH: f64 = 730.0;
+
+// What one resource costs per month, or why it could not be priced. Unknown SKUs are
+// reported as unpriced so they never silently count as free.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum LinePrice {
+    Priced {
+        meter: String,
+        unit: String,
+        unit_price: f64,
+        quantity: f64,
+        monthly_cost: f64,
+    },
+    Unpriced {
+        reason: String,
+    },
+}
+
+impl LinePrice {
+    pub fn unpriced(reason: impl Into<String>) -> Self {
+        Self::Unpriced { reason: reason.into() }
+    }
+
+    pub fn monthly_cost(&self) -> Option<f64> {
+        match self {
+            Self::Priced { monthly_cost, .. } => Some(*monthly_cost),
+            Self::Unpriced { .. } => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CostLine {
+    pub resource_key: String,
+    pub resource_type: String,
+    pub region: String,
+    pub price: LinePrice,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProviderEstimate {
+    pub provider: String,
+    pub currency: String,
+    pub lines: Vec<CostLine>,
+    // Sum of the priced lines only; see `unpriced` for what is missing
+    pub monthly_total: f64,
+    pub unpriced: Vec<String>,
+}
+
+// One row of a migration plan: what runs today and what it becomes on the target.
+// Either side may be absent for resources that are only being created or retired.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlanItem {
+    pub key: String,
+    pub current: Option<DiscoveredResource>,
+    pub target: Option<DiscoveredResource>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ComparisonLine {
+    pub key: String,
+    pub current: Option<CostLine>,
+    pub target: Option<CostLine>,
+    // target - current; None when either side is unpriced
+    pub delta: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CostComparison {
+    pub currency: String,
+    pub current: ProviderEstimate,
+    pub target: ProviderEstimate,
+    pub lines: Vec<ComparisonLine>,
+    // Sum of the line deltas that could be computed
+    pub monthly_delta: f64,
+    // Plan items left out of `monthly_delta` because a side was unpriced
+    pub incomplete: Vec<String>,
+}
+
+#[async_trait]
+pub trait PriceSource: Send + Sync {
+    fn provider(&self) -> &str;
+    fn currency(&self) -> &str;
+    async fn price(&self, resource: &DiscoveredResource, now: DateTime<Utc>) -> AppResult<LinePrice>;
+}
+
+pub(crate) fn round_cents(amount: f64) -> f64 {
+    (amount * 100.0).round() / 100.0
+}
+
+// `tiers` are (first unit of the tier, price per unit), in ascending order
+pub(crate) fn tiered_cost(tiers: &[(f64, f64)], quantity: f64) -> f64 {
+    let mut cost = 0.0;
+    for (i, (start, price)) in tiers.iter().enumerate() {
+        let end = tiers.get(i + 1).map_or(f64::INFINITY, |(next, _)| *next);
+        if quantity > *start {
+            cost += (quantity.min(end) - start) * price;
+        }
+    }
+    cost
+}
+
+async fn price_line(source: &dyn PriceSource, resource: &DiscoveredResource, now: DateTime<Utc>) -> AppResult<CostLine> {
+    Ok(CostLine {
+        resource_key: resource.resource_key.clone(),
+        resource_type: resource.resource_type.clone(),
+        region: resource.region.clone(),
+        price: source.price(resource, now).await?,
+    })
+}
+
+fn summarize(source: &dyn PriceSource, lines: Vec<CostLine>) -> ProviderEstimate {
+    let monthly_total = round_cents(lines.iter().filter_map(|l| l.price.monthly_cost()).sum());
+    let unpriced = lines
+        .iter()
+        .filter(|l| l.price.monthly_cost().is_none())
+        .map(|l| l.resource_key.clone())
+        .collect();
+    ProviderEstimate {
+        provider: source.provider().to_string(),
+        currency: source.currency().to_string(),
+        lines,
+        monthly_total,
+        unpriced,
+    }
+}
+
+pub async fn estimate(
+    source: &dyn PriceSource,
+    resources: &[DiscoveredResource],
+    now: DateTime<Utc>,
+) -> AppResult<ProviderEstimate> {
+    let mut lines = Vec::with_capacity(resources.len());
+    for resource in resources {
+        lines.push(price_line(source, resource, now).await?);
+    }
+    Ok(summarize(source, lines))
+}
+
+// Prices a plan on both providers so the current and target monthly costs can be shown
+// side by side
+pub struct CostEstimator {
+    current: Arc<dyn PriceSource>,
+    target: Arc<dyn PriceSource>,
+}
+
+impl CostEstimator {
+    pub fn new(current: Arc<dyn PriceSource>, target: Arc<dyn PriceSource>) -> Self {
+        Self { current, target }
+    }
+
+    pub async fn compare(&self, plan: &[PlanItem], now: DateTime<Utc>) -> AppResult<CostComparison> {
+        if self.current.currency() != self.target.currency() {
+            return Err(AppError::InvalidInput(format!(
+                "cannot compare {} prices with {} prices",
+                self.current.currency(),
+                self.target.currency()
+            )));
+        }
+
+        let mut keys = BTreeSet::new();
+        let mut lines = Vec::with_capacity(plan.len());
+        let (mut current_lines, mut target_lines) = (Vec::new(), Vec::new());
+        for item in plan {
+            if !keys.insert(item.key.as_str()) {
+                return Err(AppError::InvalidInput(format!("plan item {} appears more than once", item.key)));
+            }
+            let current = match &item.current {
+                Some(resource) => Some(price_line(self.current.as_ref(), resource, now).await?),
+                None => None,
+            };
+            let target = match &item.target {
+                Some(resource) => Some(price_line(self.target.as_ref(), resource, now).await?),
+                None => None,
+            };
+            let cost = |line: &Option<CostLine>| match line {
+                Some(line) => line.price.monthly_cost(),
+                None => Some(0.0),
+            };
+            let delta = match (cost(&current), cost(&target)) {
+                (Some(before), Some(after)) => Some(round_cents(after - before)),
+                _ => None,
+            };
+            current_lines.extend(current.clone());
+            target_lines.extend(target.clone());
+            lines.push(ComparisonLine { key: item.key.clone(), current, target, delta });
+        }
+
+        let monthly_delta = round_cents(lines.iter().filter_map(|l| l.delta).sum());
+        let incomplete = lines.iter().filter(|l| l.delta.is_none()).map(|l| l.key.clone()).collect();
+        Ok(CostComparison {
+            currency: self.current.currency().to_string(),
+            current: summarize(self.current.as_ref(), current_lines),
+            target: summarize(self.target.as_ref(), target_lines),
+            lines,
+            monthly_delta,
+            incomplete,
+        })
+    }
+}
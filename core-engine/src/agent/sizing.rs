@@ -0,0 +1,437 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::agent::pricing::{round_cents, PlanItem, PriceSource};
+use crate::discovery::DiscoveredResource;
+use crate::error::{AppError, AppResult};
+
+const DEFAULT_LOOKBACK_DAYS: i64 = 14;
+const DEFAULT_MIN_HISTORY_DAYS: i64 = 7;
+const DEFAULT_HEADROOM: f64 = 0.2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Architecture {
+    X86_64,
+    Arm64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InstanceSpec {
+    pub name: String,
+    pub vcpus: f64,
+    pub memory_gib: f64,
+    pub architecture: Architecture,
+    pub local_disk_gb: f64,
+    // Sustained bandwidth, not the burst figure
+    pub network_baseline_gbps: f64,
+}
+
+impl InstanceSpec {
+    pub fn new(name: &str, vcpus: f64, memory_gib: f64, network_baseline_gbps: f64) -> Self {
+        Self {
+            name: name.to_string(),
+            vcpus,
+            memory_gib,
+            architecture: Architecture::X86_64,
+            local_disk_gb: 0.0,
+            network_baseline_gbps,
+        }
+    }
+
+    pub fn with_architecture(mut self, architecture: Architecture) -> Self {
+        self.architecture = architecture;
+        self
+    }
+
+    pub fn with_local_disk(mut self, gb: f64) -> Self {
+        self.local_disk_gb = gb;
+        self
+    }
+}
+
+// The sizes one provider offers, and how a size is expressed on a resource so it can be
+// priced (`instance_type` on EC2, `vm_size` on Azure VMs)
+#[derive(Debug, Clone)]
+pub struct InstanceCatalog {
+    resource_type: String,
+    size_attribute: String,
+    specs: Vec<InstanceSpec>,
+}
+
+impl InstanceCatalog {
+    pub fn new(resource_type: &str, size_attribute: &str) -> Self {
+        Self {
+            resource_type: resource_type.to_string(),
+            size_attribute: size_attribute.to_string(),
+            specs: Vec::new(),
+        }
+    }
+
+    pub fn with_instance(mut self, spec: InstanceSpec) -> Self {
+        self.specs.push(spec);
+        self
+    }
+
+    pub fn aws() -> Self {
+        let arm = |spec: InstanceSpec| spec.with_architecture(Architecture::Arm64);
+        Self::new("ec2:instance", "instance_type")
+            .with_instance(InstanceSpec::new("t3.micro", 2.0, 1.0, 0.064))
+            .with_instance(InstanceSpec::new("t3.small", 2.0, 2.0, 0.128))
+            .with_instance(InstanceSpec::new("t3.medium", 2.0, 4.0, 0.256))
+            .with_instance(InstanceSpec::new("t3.large", 2.0, 8.0, 0.512))
+            .with_instance(InstanceSpec::new("m5.large", 2.0, 8.0, 0.75))
+            .with_instance(InstanceSpec::new("m5.xlarge", 4.0, 16.0, 1.25))
+            .with_instance(InstanceSpec::new("m5.2xlarge", 8.0, 32.0, 2.5))
+            .with_instance(InstanceSpec::new("m5d.large", 2.0, 8.0, 0.75).with_local_disk(75.0))
+            .with_instance(InstanceSpec::new("m5d.xlarge", 4.0, 16.0, 1.25).with_local_disk(150.0))
+            .with_instance(arm(InstanceSpec::new("m6g.large", 2.0, 8.0, 0.75)))
+            .with_instance(arm(InstanceSpec::new("m6g.xlarge", 4.0, 16.0, 1.25)))
+            .with_instance(InstanceSpec::new("c5.large", 2.0, 4.0, 0.75))
+            .with_instance(InstanceSpec::new("c5.xlarge", 4.0, 8.0, 1.25))
+            .with_instance(InstanceSpec::new("r5.large", 2.0, 16.0, 0.75))
+            .with_instance(InstanceSpec::new("r5.xlarge", 4.0, 32.0, 1.25))
+    }
+
+    pub fn azure() -> Self {
+        Self::new("microsoft.compute/virtualmachines", "vm_size")
+            .with_instance(InstanceSpec::new("Standard_B2s", 2.0, 4.0, 0.2))
+            .with_instance(InstanceSpec::new("Standard_D2s_v3", 2.0, 8.0, 1.0))
+            .with_instance(InstanceSpec::new("Standard_D4s_v3", 4.0, 16.0, 2.0))
+            .with_instance(InstanceSpec::new("Standard_D8s_v3", 8.0, 32.0, 4.0))
+            .with_instance(InstanceSpec::new("Standard_D2ds_v5", 2.0, 8.0, 12.5).with_local_disk(75.0))
+            .with_instance(InstanceSpec::new("Standard_D2ps_v5", 2.0, 8.0, 12.5).with_architecture(Architecture::Arm64))
+            .with_instance(InstanceSpec::new("Standard_F2s_v2", 2.0, 4.0, 0.875))
+            .with_instance(InstanceSpec::new("Standard_E2s_v3", 2.0, 16.0, 1.0))
+            .with_instance(InstanceSpec::new("Standard_E4s_v3", 4.0, 32.0, 2.0))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&InstanceSpec> {
+        self.specs.iter().find(|s| s.name == name)
+    }
+
+    fn resource(&self, key: &str, region: &str, spec: &InstanceSpec) -> DiscoveredResource {
+        DiscoveredResource::new(&self.resource_type, key, None, region).with_attribute(self.size_attribute.as_str(), spec.name.as_str())
+    }
+}
+
+// One utilization reading, as pulled from CloudWatch or the compute-manager collector.
+// CloudWatch does not report memory without the agent, so memory is optional.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UtilizationSample {
+    pub timestamp: DateTime<Utc>,
+    pub cpu_percent: f64,
+    pub memory_percent: Option<f64>,
+}
+
+impl UtilizationSample {
+    // Accepts both the collector's metric names and CloudWatch's
+    pub fn from_metrics(timestamp: DateTime<Utc>, metrics: &HashMap<String, f64>) -> Option<Self> {
+        let metric = |names: &[&str]| names.iter().find_map(|n| metrics.get(*n).copied());
+        Some(Self {
+            timestamp,
+            cpu_percent: metric(&["cpu_utilization", "CPUUtilization"])?,
+            memory_percent: metric(&["memory_utilization", "mem_used_percent"]),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ObservedUtilization {
+    pub samples: usize,
+    pub p95_cpu_percent: f64,
+    pub max_memory_percent: Option<f64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SizingPolicy {
+    pub lookback: Duration,
+    // Resources observed for less than this are sized like-for-like
+    pub min_history: Duration,
+    pub cpu_headroom: f64,
+    pub memory_headroom: f64,
+    pub allow_architecture_change: bool,
+}
+
+impl Default for SizingPolicy {
+    fn default() -> Self {
+        Self {
+            lookback: Duration::days(DEFAULT_LOOKBACK_DAYS),
+            min_history: Duration::days(DEFAULT_MIN_HISTORY_DAYS),
+            cpu_headroom: DEFAULT_HEADROOM,
+            memory_headroom: DEFAULT_HEADROOM,
+            allow_architecture_change: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SizingConstraints {
+    pub min_network_gbps: f64,
+    // Where the target runs, when it is not the source's region (e.g. moving to Azure)
+    pub target_region: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "basis", rename_all = "snake_case")]
+pub enum SizingBasis {
+    Utilization,
+    // Not enough metric history to size from, so the target matches the source's capacity
+    LikeForLike { reason: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SizingRecommendation {
+    pub resource_key: String,
+    pub region: String,
+    pub source_type: String,
+    pub target_region: String,
+    pub observed: Option<ObservedUtilization>,
+    pub basis: SizingBasis,
+    pub recommended_type: Option<String>,
+    pub current_monthly: Option<f64>,
+    pub recommended_monthly: Option<f64>,
+    pub monthly_savings: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SizingPlan {
+    pub recommendations: Vec<SizingRecommendation>,
+    pub monthly_savings: f64,
+}
+
+// Picks the cheapest target size that covers a resource's observed load rather than
+// mapping its instance type across one to one
+pub struct RightSizer {
+    source: InstanceCatalog,
+    target: InstanceCatalog,
+    current_prices: Arc<dyn PriceSource>,
+    target_prices: Arc<dyn PriceSource>,
+    policy: SizingPolicy,
+}
+
+impl RightSizer {
+    pub fn new(
+        source: InstanceCatalog,
+        target: InstanceCatalog,
+        current_prices: Arc<dyn PriceSource>,
+        target_prices: Arc<dyn PriceSource>,
+    ) -> Self {
+        Self { source, target, current_prices, target_prices, policy: SizingPolicy::default() }
+    }
+
+    pub fn with_policy(mut self, policy: SizingPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    fn observe(&self, history: &[UtilizationSample], now: DateTime<Utc>) -> Option<ObservedUtilization> {
+        let window: Vec<&UtilizationSample> =
+            history.iter().filter(|s| s.timestamp <= now && now - s.timestamp <= self.policy.lookback).collect();
+        if window.is_empty() {
+            return None;
+        }
+        let mut cpu: Vec<f64> = window.iter().map(|s| s.cpu_percent).collect();
+        cpu.sort_by(f64::total_cmp);
+        // Nearest-rank percentile
+        let rank = ((cpu.len() as f64) * 0.95).ceil() as usize;
+        Some(ObservedUtilization {
+            samples: window.len(),
+            p95_cpu_percent: cpu[rank.max(1) - 1],
+            max_memory_percent: window.iter().filter_map(|s| s.memory_percent).reduce(f64::max),
+        })
+    }
+
+    fn history_gap(&self, history: &[UtilizationSample], now: DateTime<Utc>) -> Option<String> {
+        let oldest = history.iter().filter(|s| now - s.timestamp <= self.policy.lookback).map(|s| s.timestamp).min();
+        match oldest {
+            None => Some("no utilization history".to_string()),
+            Some(oldest) if now - oldest < self.policy.min_history => Some(format!(
+                "only {} hours of utilization history, need {}",
+                (now - oldest).num_hours(),
+                self.policy.min_history.num_hours()
+            )),
+            Some(_) => None,
+        }
+    }
+
+    pub async fn recommend(
+        &self,
+        resource: &DiscoveredResource,
+        history: &[UtilizationSample],
+        constraints: &SizingConstraints,
+        now: DateTime<Utc>,
+    ) -> AppResult<SizingRecommendation> {
+        let source_type = resource
+            .attributes
+            .get(&self.source.size_attribute)
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| AppError::InvalidInput(format!("{} has no {}", resource.resource_key, self.source.size_attribute)))?;
+        let source = self
+            .source
+            .get(source_type)
+            .ok_or_else(|| AppError::InvalidInput(format!("unknown source size {}", source_type)))?;
+
+        let observed = self.observe(history, now);
+        let (basis, vcpus, memory_gib) = match (self.history_gap(history, now), &observed) {
+            (None, Some(observed)) => (
+                SizingBasis::Utilization,
+                source.vcpus * observed.p95_cpu_percent / 100.0 * (1.0 + self.policy.cpu_headroom),
+                // Without memory metrics keep the memory the workload has today
+                observed.max_memory_percent.map_or(source.memory_gib, |max| {
+                    source.memory_gib * max / 100.0 * (1.0 + self.policy.memory_headroom)
+                }),
+            ),
+            (gap, _) => (
+                SizingBasis::LikeForLike { reason: gap.unwrap_or_default() },
+                source.vcpus,
+                source.memory_gib,
+            ),
+        };
+
+        let candidates = self.target.specs.iter().filter(|t| {
+            t.vcpus >= vcpus
+                && t.memory_gib >= memory_gib
+                && t.network_baseline_gbps >= constraints.min_network_gbps
+                && (self.policy.allow_architecture_change || t.architecture == source.architecture)
+                // Workloads on instance storage need a target that has it too
+                && (source.local_disk_gb == 0.0 || t.local_disk_gb > 0.0)
+        });
+        let target_region = constraints.target_region.clone().unwrap_or_else(|| resource.region.clone());
+        let mut best: Option<(&InstanceSpec, f64)> = None;
+        for candidate in candidates {
+            let priced = self.target.resource(&resource.resource_key, &target_region, candidate);
+            let Some(cost) = self.target_prices.price(&priced, now).await?.monthly_cost() else { continue };
+            let cheaper = best.is_none_or(|(spec, best_cost)| {
+                cost < best_cost || (cost == best_cost && candidate.vcpus < spec.vcpus)
+            });
+            if cheaper {
+                best = Some((candidate, cost));
+            }
+        }
+
+        let current_monthly = self.current_prices.price(resource, now).await?.monthly_cost();
+        let recommended_monthly = best.map(|(_, cost)| cost);
+        Ok(SizingRecommendation {
+            resource_key: resource.resource_key.clone(),
+            region: resource.region.clone(),
+            source_type: source_type.to_string(),
+            target_region,
+            observed,
+            basis,
+            recommended_type: best.map(|(spec, _)| spec.name.clone()),
+            current_monthly,
+            recommended_monthly,
+            monthly_savings: current_monthly.zip(recommended_monthly).map(|(now, next)| round_cents(now - next)),
+        })
+    }
+
+    // `history` is keyed by resource_key
+    pub async fn plan(
+        &self,
+        resources: &[DiscoveredResource],
+        history: &HashMap<String, Vec<UtilizationSample>>,
+        constraints: &SizingConstraints,
+        now: DateTime<Utc>,
+    ) -> AppResult<SizingPlan> {
+        let mut recommendations = Vec::with_capacity(resources.len());
+        for resource in resources {
+            let samples = history.get(&resource.resource_key).map(Vec::as_slice).unwrap_or_default();
+            recommendations.push(self.recommend(resource, samples, constraints, now).await?);
+        }
+        let monthly_savings = round_cents(recommendations.iter().filter_map(|r| r.monthly_savings).sum());
+        Ok(SizingPlan { recommendations, monthly_savings })
+    }
+
+    // The recommendation as a row for `CostEstimator::compare`
+    pub fn plan_item(&self, resource: &DiscoveredResource, recommendation: &SizingRecommendation) -> PlanItem {
+        let target = recommendation
+            .recommended_type
+            .as_deref()
+            .and_then(|name| self.target.get(name))
+            .map(|spec| self.target.resource(&resource.resource_key, &recommendation.target_region, spec));
+        PlanItem { key: resource.resource_key.clone(), current: Some(resource.clone()), target }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::pricing::AwsPriceTable;
+
+    fn now() -> DateTime<Utc> {
+        "2026-10-01T00:00:00Z".parse().unwrap()
+    }
+
+    // Two weeks of hourly samples: 20% CPU, a busier 30% stretch and three 90% spikes that
+    // p95 should ignore. Memory peaks at 40%.
+    fn fixture_history(days: i64) -> Vec<UtilizationSample> {
+        let hours = days * 24;
+        let mut samples: Vec<UtilizationSample> = (0..hours)
+            .map(|h| UtilizationSample {
+                timestamp: now() - Duration::hours(hours - h),
+                cpu_percent: match h % 112 {
+                    0 => 90.0,
+                    1..=7 => 30.0,
+                    _ => 20.0,
+                },
+                memory_percent: Some(if h == 5 { 40.0 } else { 35.0 }),
+            })
+            .collect();
+        // Outside the lookback window, so it must not count
+        samples.push(UtilizationSample { timestamp: now() - Duration::days(30), cpu_percent: 100.0, memory_percent: Some(100.0) });
+        samples
+    }
+
+    fn instance(id: &str, instance_type: &str) -> DiscoveredResource {
+        DiscoveredResource::new("ec2:instance", id, None, "us-east-1").with_attribute("instance_type", instance_type)
+    }
+
+    fn sizer(policy: SizingPolicy) -> RightSizer {
+        let prices: Arc<dyn PriceSource> = Arc::new(AwsPriceTable::new());
+        RightSizer::new(InstanceCatalog::aws(), InstanceCatalog::aws(), prices.clone(), prices).with_policy(policy)
+    }
+
+    #[tokio::test]
+    async fn test_picks_cheapest_type_that_satisfies_constraints() {
+        let history = fixture_history(14);
+        let open = SizingConstraints::default();
+
+        // 4 vCPU at p95 30% plus 20% headroom needs 1.44 vCPU and 7.68 GiB
+        let rec = sizer(SizingPolicy::default()).recommend(&instance("web", "m5.xlarge"), &history, &open, now()).await.unwrap();
+        let observed = rec.observed.clone().unwrap();
+        assert_eq!((observed.samples, observed.p95_cpu_percent, observed.max_memory_percent), (336, 30.0, Some(40.0)));
+        assert_eq!(rec.basis, SizingBasis::Utilization);
+        assert_eq!(rec.recommended_type.as_deref(), Some("t3.large"));
+        assert_eq!(rec.monthly_savings, Some(79.42));
+
+        let networked = SizingConstraints { min_network_gbps: 0.75, ..SizingConstraints::default() };
+        let rec = sizer(SizingPolicy::default()).recommend(&instance("web", "m5.xlarge"), &history, &networked, now()).await.unwrap();
+        assert_eq!(rec.recommended_type.as_deref(), Some("m5.large"));
+
+        let arm = SizingPolicy { allow_architecture_change: true, ..SizingPolicy::default() };
+        let rec = sizer(arm).recommend(&instance("web", "m5.xlarge"), &history, &networked, now()).await.unwrap();
+        assert_eq!(rec.recommended_type.as_deref(), Some("m6g.large"));
+
+        let rec = sizer(SizingPolicy::default()).recommend(&instance("cache", "m5d.xlarge"), &history, &open, now()).await.unwrap();
+        assert_eq!(rec.recommended_type.as_deref(), Some("m5d.large"));
+    }
+
+    #[tokio::test]
+    async fn test_short_history_falls_back_to_like_for_like() {
+        let mut history = HashMap::new();
+        history.insert("ec2:instance/new".to_string(), fixture_history(2));
+        history.insert("ec2:instance/web".to_string(), fixture_history(14));
+        let resources = vec![instance("new", "m5.xlarge"), instance("web", "m5.xlarge")];
+
+        let plan = sizer(SizingPolicy::default()).plan(&resources, &history, &SizingConstraints::default(), now()).await.unwrap();
+        let new = &plan.recommendations[0];
+        assert!(matches!(new.basis, SizingBasis::LikeForLike { .. }));
+        assert_eq!(new.recommended_type.as_deref(), Some("m5.xlarge"));
+        assert_eq!(new.monthly_savings, Some(0.0));
+        assert_eq!(plan.monthly_savings, 79.42);
+    }
+}
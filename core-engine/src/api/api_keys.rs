@@ -0,0 +1,96 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    db::DbPool,
+    error::{AppError, AppResult},
+    middleware::{ApiKeyService, AuthUser, Scope},
+    models::ApiKey,
+};
+
+use super::audit::record_audit;
+
+#[derive(Debug, Deserialize)]
+pub struct CreateApiKeyRequest {
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+// The only response that ever carries the raw key
+#[derive(Debug, Serialize)]
+pub struct IssuedApiKeyResponse {
+    #[serde(flatten)]
+    pub key: ApiKey,
+    pub secret: String,
+}
+
+#[axum::debug_handler]
+pub async fn create_api_key_handler(
+    State(db): State<DbPool>,
+    Extension(keys): Extension<Arc<ApiKeyService>>,
+    auth: AuthUser,
+    Json(payload): Json<CreateApiKeyRequest>,
+) -> AppResult<(StatusCode, Json<IssuedApiKeyResponse>)> {
+    auth.require(Scope::ManageApiKeys)?;
+    auth.require_direct("Changing API keys")?;
+    // A key can't mint another key with more access than it has itself
+    if let Some(scope) = payload.scopes.iter().find(|s| !auth.scopes.contains(s)) {
+        return Err(AppError::Forbidden(format!("Cannot grant scope {} you don't hold", scope)));
+    }
+
+    let (key, secret) = keys
+        .issue(auth.user_id, auth.user_id, &payload.name, &payload.scopes, payload.expires_at, Utc::now())
+        .await?;
+    record_audit(&db, &auth, "api_key.create", Some(key.id), json!({ "name": key.name, "scopes": key.scopes })).await;
+
+    Ok((StatusCode::CREATED, Json(IssuedApiKeyResponse { key, secret })))
+}
+
+#[axum::debug_handler(state = DbPool)]
+pub async fn list_api_keys_handler(
+    Extension(keys): Extension<Arc<ApiKeyService>>,
+    auth: AuthUser,
+) -> AppResult<Json<Vec<ApiKey>>> {
+    auth.require(Scope::ManageApiKeys)?;
+    Ok(Json(keys.list(auth.user_id).await?))
+}
+
+#[axum::debug_handler]
+pub async fn rotate_api_key_handler(
+    State(db): State<DbPool>,
+    Extension(keys): Extension<Arc<ApiKeyService>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<IssuedApiKeyResponse>> {
+    auth.require(Scope::ManageApiKeys)?;
+    auth.require_direct("Changing API keys")?;
+    let (key, secret) = keys.rotate(auth.user_id, id).await?;
+    record_audit(&db, &auth, "api_key.rotate", Some(id), json!({ "prefix": key.prefix })).await;
+
+    Ok(Json(IssuedApiKeyResponse { key, secret }))
+}
+
+#[axum::debug_handler]
+pub async fn revoke_api_key_handler(
+    State(db): State<DbPool>,
+    Extension(keys): Extension<Arc<ApiKeyService>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<()>> {
+    auth.require(Scope::ManageApiKeys)?;
+    auth.require_direct("Changing API keys")?;
+    keys.revoke(auth.user_id, id, Utc::now()).await?;
+    record_audit(&db, &auth, "api_key.revoke", Some(id), json!({})).await;
+
+    Ok(Json(()))
+}
@@ -0,0 +1,61 @@
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::{
+    db::DbPool,
+    error::AppResult,
+    middleware::{AuthUser, Scope},
+    models::{AuditEvent, AuditFilter},
+};
+
+const DEFAULT_LIMIT: i64 = 100;
+const MAX_LIMIT: i64 = 1000;
+
+#[derive(Debug, Deserialize)]
+pub struct ListParams {
+    pub limit: Option<i64>,
+}
+
+// Owner and actor for an event; while impersonating the event stays in the customer's log
+// but names the admin as actor, and the details carry both identities
+pub(crate) fn audit_identity(auth: &AuthUser, details: &mut Value) -> (Uuid, Uuid) {
+    if let Value::Object(fields) = details {
+        if let Some(key_id) = auth.api_key_id {
+            fields.insert("api_key_id".into(), Value::String(key_id.to_string()));
+        }
+        if let Some(impersonator) = auth.impersonator {
+            fields.insert("impersonated_user_id".into(), Value::String(auth.user_id.to_string()));
+            fields.insert("impersonator_id".into(), Value::String(impersonator.actor_id.to_string()));
+            fields.insert("impersonation_session_id".into(), Value::String(impersonator.session_id.to_string()));
+        }
+    }
+    (auth.user_id, auth.actor_id())
+}
+
+// A failed audit write is logged rather than failing the change it describes
+pub(crate) async fn record_audit(db: &DbPool, auth: &AuthUser, action: &str, target_id: Option<Uuid>, mut details: Value) {
+    let (owner_id, actor_id) = audit_identity(auth, &mut details);
+    let target_type = action.split('.').next().unwrap_or(action);
+    if let Err(e) = AuditEvent::record(db, owner_id, actor_id, action, target_type, target_id, details).await {
+        warn!("Failed to record audit event {} for {:?}: {}", action, target_id, e);
+    }
+}
+
+#[axum::debug_handler]
+pub async fn list_audit_handler(
+    State(db): State<DbPool>,
+    auth: AuthUser,
+    Query(filter): Query<AuditFilter>,
+    Query(params): Query<ListParams>,
+) -> AppResult<Json<Vec<AuditEvent>>> {
+    auth.require(Scope::ReadAudit)?;
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let events = AuditEvent::find(&db, auth.user_id, &filter, limit).await?;
+    Ok(Json(events))
+}
@@ -1,23 +1,34 @@
+use std::sync::Arc;
+
 use axum::{
-    extract::State,
-    Json,
+    extract::{Form, Path, State},
+    http::{HeaderMap, StatusCode},
+    Extension, Json,
 };
+use chrono::Utc;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 use sqlx::PgPool; // CockroachDB uses PostgreSQL protocol
+use uuid::Uuid;
 use validator::Validate;
 
 use crate::{
+    device::{DeviceAuthService, DeviceAuthorization, DeviceCodeResponse, DeviceGrantError, DEVICE_CODE_GRANT},
     error::AppResult,
+    middleware::{auth::issue_access_token, AuthUser, Scope},
     models::user::User,
+    password::LocalAuthService,
 };
 
+use super::audit::record_audit;
+
 #[derive(Debug, Deserialize, Validate, Clone)]
 pub struct RegisterRequest {
     #[validate(length(min = 1))]
     pub name: String,
     #[validate(email)]
     pub email: String,
-    #[validate(length(min = 8))]
+    // Checked against the password policy rather than here, so every reason is reported
     pub password: String,
 }
 
@@ -32,32 +43,44 @@ pub struct AuthResponse {
     pub user: User,
 }
 
-#[axum::debug_handler]
+#[derive(Debug, Deserialize)]
+pub struct ChangePasswordRequest {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UnlockRequest {
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RedeemUnlockRequest {
+    pub token: String,
+}
+
+// The load balancer appends the address it saw, so the last hop is the one to trust
+fn client_ip(headers: &HeaderMap) -> Option<String> {
+    let forwarded = headers
+        .get("x-forwarded-for")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.rsplit(',').next())
+        .map(str::trim)
+        .filter(|v| !v.is_empty());
+    forwarded
+        .or_else(|| headers.get("x-real-ip").and_then(|v| v.to_str().ok()).map(str::trim))
+        .map(str::to_string)
+}
+
+#[axum::debug_handler(state = PgPool)]
 pub async fn register_handler(
-    State(pool): State<PgPool>,
+    Extension(passwords): Extension<Arc<LocalAuthService>>,
     Json(payload): Json<RegisterRequest>,
 ) -> AppResult<Json<AuthResponse>> {
     // Validate request
     payload.validate().map_err(|e| crate::error::AppError::Validation(e.to_string()))?;
 
-    // Hash password
-    use argon2::{Argon2, PasswordHasher};
-    use argon2::password_hash::SaltString;
-    use argon2::password_hash::rand_core::OsRng;
-    
-    let salt = SaltString::generate(&mut OsRng);
-    let argon2 = Argon2::default();
-    let password_hash = argon2.hash_password(payload.password.as_bytes(), &salt)
-        .map_err(|e| crate::error::AppError::Internal(e.to_string()))?;
-    let password_hash = password_hash.to_string();
-
-    // Create user
-    let user = User::create(
-        &pool,
-        &payload.name,
-        &payload.email,
-        &password_hash,
-    ).await?;
+    let user = passwords.register(&payload.name, &payload.email, &payload.password).await?;
 
     // Generate JWT token
     let token = create_jwt_token(&user)?;
@@ -65,28 +88,14 @@ pub async fn register_handler(
     Ok(Json(AuthResponse { token, user }))
 }
 
-#[axum::debug_handler]
+#[axum::debug_handler(state = PgPool)]
 pub async fn login_handler(
-    State(pool): State<PgPool>,
+    Extension(passwords): Extension<Arc<LocalAuthService>>,
+    headers: HeaderMap,
     Json(payload): Json<LoginRequest>,
 ) -> AppResult<Json<AuthResponse>> {
-    // Find user by email
-    let user = User::find_by_email(&pool, &payload.email)
-        .await?
-        .ok_or_else(|| crate::error::AppError::Auth("Invalid email or password".into()))?;
-
-    // Verify password
-    use argon2::{Argon2, PasswordVerifier};
-    use argon2::password_hash::PasswordHash;
-    
-    let parsed_hash = PasswordHash::new(&user.password_hash)
-        .map_err(|e| crate::error::AppError::Internal(e.to_string()))?;
-    let argon2 = Argon2::default();
-    let is_valid = argon2.verify_password(payload.password.as_bytes(), &parsed_hash).is_ok();
-
-    if !is_valid {
-        return Err(crate::error::AppError::Auth("Invalid email or password".into()));
-    }
+    let ip = client_ip(&headers);
+    let user = passwords.authenticate(&payload.email, &payload.password, ip.as_deref(), Utc::now()).await?;
 
     // Generate JWT token
     let token = create_jwt_token(&user)?;
@@ -94,41 +103,139 @@ pub async fn login_handler(
     Ok(Json(AuthResponse { token, user }))
 }
 
-fn create_jwt_token(user: &User) -> AppResult<String> {
-    use jsonwebtoken::{encode, EncodingKey, Header};
-    use serde::{Deserialize, Serialize};
-    use time::OffsetDateTime;
-
-    #[derive(Debug, Serialize, Deserialize)]
-    struct Claims {
-        sub: String, // User ID
-        exp: i64,    // Expiration time
-        iat: i64,    // Issued at
+#[axum::debug_handler]
+pub async fn change_password_handler(
+    State(pool): State<PgPool>,
+    Extension(passwords): Extension<Arc<LocalAuthService>>,
+    auth: AuthUser,
+    Json(payload): Json<ChangePasswordRequest>,
+) -> AppResult<StatusCode> {
+    if auth.api_key_id.is_some() {
+        return Err(crate::error::AppError::Forbidden("Passwords can only be changed by a signed-in user".into()));
     }
+    auth.require_direct("Changing a password")?;
+    passwords.change_password(auth.user_id, &payload.current_password, &payload.new_password, Utc::now()).await?;
+    record_audit(&pool, &auth, "user.password_change", Some(auth.user_id), json!({})).await;
+    Ok(StatusCode::NO_CONTENT)
+}
 
-    let now = OffsetDateTime::now_utc();
-    let expiry = now + time::Duration::hours(24); // Token expires in 24 hours
+// Always accepted, so the response doesn't reveal whether the account exists or is locked
+#[axum::debug_handler(state = PgPool)]
+pub async fn request_unlock_handler(
+    Extension(passwords): Extension<Arc<LocalAuthService>>,
+    Json(payload): Json<UnlockRequest>,
+) -> StatusCode {
+    if let Err(e) = passwords.request_unlock(&payload.email, Utc::now()).await {
+        tracing::warn!("Failed to issue an unlock link: {}", e);
+    }
+    StatusCode::ACCEPTED
+}
 
-    let claims = Claims {
-        sub: user.id.to_string(),
-        exp: expiry.unix_timestamp(),
-        iat: now.unix_timestamp(),
-    };
+#[axum::debug_handler(state = PgPool)]
+pub async fn redeem_unlock_handler(
+    Extension(passwords): Extension<Arc<LocalAuthService>>,
+    Json(payload): Json<RedeemUnlockRequest>,
+) -> AppResult<StatusCode> {
+    passwords.redeem_unlock(&payload.token, Utc::now()).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
 
-    let token = encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(b"your-secret-key"), // In production, use a proper secret
-    ).map_err(|e| crate::error::AppError::Internal(e.to_string()))?;
+#[axum::debug_handler]
+pub async fn admin_unlock_handler(
+    State(pool): State<PgPool>,
+    Extension(passwords): Extension<Arc<LocalAuthService>>,
+    auth: AuthUser,
+    Path(user_id): Path<Uuid>,
+) -> AppResult<StatusCode> {
+    auth.require(Scope::ManageTenants)?;
+    let user = passwords.admin_unlock(user_id).await?;
+    record_audit(&pool, &auth, "user.unlock", Some(user.id), json!({ "email": user.email })).await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Registered users get the standard role until roles are stored per user
+fn create_jwt_token(user: &User) -> AppResult<String> {
+    issue_access_token(user.id, "user").map(|(token, _)| token)
+}
 
-    Ok(token)
+#[derive(Debug, Deserialize)]
+pub struct DeviceCodeRequest {
+    pub client_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VerifyDeviceRequest {
+    pub user_code: String,
+    // false turns the device away
+    #[serde(default = "default_approve")]
+    pub approve: bool,
+}
+
+fn default_approve() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TokenRequest {
+    pub grant_type: String,
+    pub device_code: Option<String>,
+    pub client_id: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub token_type: &'static str,
+    pub expires_in: i64,
+}
+
+// Device authorization request; form-encoded as RFC 8628 specifies
+#[axum::debug_handler(state = PgPool)]
+pub async fn device_code_handler(
+    Extension(devices): Extension<Arc<DeviceAuthService>>,
+    Form(payload): Form<DeviceCodeRequest>,
+) -> AppResult<Json<DeviceCodeResponse>> {
+    Ok(Json(devices.start(&payload.client_id, Utc::now()).await?))
+}
+
+#[axum::debug_handler]
+pub async fn verify_device_handler(
+    State(pool): State<PgPool>,
+    Extension(devices): Extension<Arc<DeviceAuthService>>,
+    auth: AuthUser,
+    Json(payload): Json<VerifyDeviceRequest>,
+) -> AppResult<Json<DeviceAuthorization>> {
+    // API keys and impersonation tokens can't sign a device in as someone
+    if auth.api_key_id.is_some() {
+        return Err(crate::error::AppError::Forbidden("Devices must be approved by a signed-in user".into()));
+    }
+    auth.require_direct("Approving a device")?;
+    let authorization = devices.verify(auth.user_id, &payload.user_code, payload.approve, Utc::now()).await?;
+    let action = if payload.approve { "device.approve" } else { "device.deny" };
+    record_audit(&pool, &auth, action, Some(authorization.id), json!({ "client_id": authorization.client_id })).await;
+    Ok(Json(authorization))
+}
+
+// Polled by the device until the user has approved it
+#[axum::debug_handler(state = PgPool)]
+pub async fn token_handler(
+    Extension(devices): Extension<Arc<DeviceAuthService>>,
+    Form(payload): Form<TokenRequest>,
+) -> Result<Json<TokenResponse>, DeviceGrantError> {
+    if payload.grant_type != DEVICE_CODE_GRANT {
+        return Err(DeviceGrantError::UnsupportedGrantType);
+    }
+    let device_code = payload.device_code.as_deref().ok_or(DeviceGrantError::InvalidGrant)?;
+    let user_id = devices.poll(device_code, payload.client_id.as_deref().unwrap_or_default(), Utc::now()).await?;
+    let (access_token, expires_in) = issue_access_token(user_id, "user")?;
+    Ok(Json(TokenResponse { access_token, token_type: "Bearer", expires_in }))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 use sqlx::postgres::PgPoolOptions; // CockroachDB connection
-    use uuid::Uuid;
+    use crate::password::{LoginThrottle, PgCredentialStore, PgLockoutStore};
 
     async fn setup() -> PgPool {
         let db_url = std::env::var("DATABASE_URL")
@@ -149,18 +256,23 @@ use sqlx::postgres::PgPoolOptions; // CockroachDB connection
         pool
     }
 
+    fn passwords(pool: &PgPool) -> Extension<Arc<LocalAuthService>> {
+        let throttle = LoginThrottle::new(Arc::new(PgLockoutStore::new(pool.clone())));
+        Extension(Arc::new(LocalAuthService::new(Arc::new(PgCredentialStore::new(pool.clone())), throttle)))
+    }
+
     #[tokio::test]
     async fn test_register_handler() {
         let pool = setup().await;
 
         let request = RegisterRequest {
             email: format!("test{}@example.com", Uuid::new_v4()),
-            password: "password123".to_string(),
+            password: "correct-horse-battery".to_string(),
             name: "Test User".to_string(),
         };
 
         let result = register_handler(
-            State(pool.clone()),
+            passwords(&pool),
             Json(request.clone()),
         ).await;
 
@@ -177,7 +289,7 @@ use sqlx::postgres::PgPoolOptions; // CockroachDB connection
 
         // First register a user
         let email = format!("test{}@example.com", Uuid::new_v4());
-        let password = "password123".to_string();
+        let password = "correct-horse-battery".to_string();
         let request = RegisterRequest {
             email: email.clone(),
             password: password.clone(),
@@ -185,7 +297,7 @@ use sqlx::postgres::PgPoolOptions; // CockroachDB connection
         };
 
         let _ = register_handler(
-            State(pool.clone()),
+            passwords(&pool),
             Json(request),
         ).await.unwrap();
 
@@ -196,7 +308,8 @@ use sqlx::postgres::PgPoolOptions; // CockroachDB connection
         };
 
         let result = login_handler(
-            State(pool.clone()),
+            passwords(&pool),
+            HeaderMap::new(),
             Json(login_request),
         ).await;
 
@@ -0,0 +1,303 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Path, Query, State,
+    },
+    http::StatusCode,
+    response::Response,
+    Extension, Json,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::{
+    bastion::{RecordedChunk, SessionBroker, SessionFilter, SessionGrant, SessionStatus, SshSession, StartSession},
+    db::DbPool,
+    error::{AppError, AppResult},
+    middleware::{AuthUser, Scope},
+};
+
+use super::audit::record_audit;
+
+const DEFAULT_LIMIT: i64 = 50;
+const BRIDGE_BUFFER: usize = 8 * 1024;
+
+#[derive(Debug, Deserialize)]
+pub struct ListSessionsParams {
+    pub tenant_id: Option<Uuid>,
+    pub status: Option<SessionStatus>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CaResponse {
+    // OpenSSH public keys sshd should trust, current key first
+    pub keys: Vec<String>,
+    pub trust_script: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecordingResponse {
+    pub session: SshSession,
+    pub chunks: Vec<RecordedChunk>,
+}
+
+fn broker(bastion: Option<Extension<Arc<SessionBroker>>>) -> AppResult<Arc<SessionBroker>> {
+    bastion
+        .map(|Extension(broker)| broker)
+        .ok_or_else(|| AppError::Configuration("SSH sessions are not configured".into()))
+}
+
+#[axum::debug_handler]
+pub async fn start_session_handler(
+    State(db): State<DbPool>,
+    bastion: Option<Extension<Arc<SessionBroker>>>,
+    auth: AuthUser,
+    Path((fleet_id, group_id, instance_id)): Path<(String, String, String)>,
+    Json(request): Json<StartSession>,
+) -> AppResult<(StatusCode, Json<SessionGrant>)> {
+    auth.require(Scope::StartSessions)?;
+    auth.require_direct("Starting SSH sessions")?;
+    let broker = broker(bastion)?;
+
+    let grant = broker
+        .start_session(auth.user_id, auth.actor_id(), &fleet_id, &group_id, &instance_id, request, Utc::now())
+        .await?;
+    let session = &grant.session;
+    record_audit(
+        &db,
+        &auth,
+        "ssh.session_start",
+        Some(session.id),
+        json!({
+            "fleet_id": session.fleet_id,
+            "group_id": session.group_id,
+            "instance_id": session.instance_id,
+            "login": session.login,
+            "mode": session.mode,
+            "command": session.command,
+            "certificate_serial": session.certificate_serial,
+            "expires_at": session.expires_at,
+        }),
+    )
+    .await;
+    Ok((StatusCode::CREATED, Json(grant)))
+}
+
+// Upgrades to a WebSocket carrying the terminal stream of a recorded session as binary frames
+#[axum::debug_handler(state = DbPool)]
+pub async fn attach_session_handler(
+    bastion: Option<Extension<Arc<SessionBroker>>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+    ws: WebSocketUpgrade,
+) -> AppResult<Response> {
+    auth.require(Scope::StartSessions)?;
+    auth.require_direct("Attaching to SSH sessions")?;
+    let broker = broker(bastion)?;
+    // Checked again by `attach`; failing here answers with an error instead of a closed socket
+    let session = broker.session(id, Utc::now()).await?;
+    if session.tenant_id != auth.user_id || session.user_id != auth.actor_id() {
+        return Err(AppError::NotFound(format!("SSH session {} not found", id)));
+    }
+
+    let (tenant_id, user_id) = (auth.user_id, auth.actor_id());
+    Ok(ws.on_upgrade(move |socket| bridge(broker, tenant_id, user_id, id, socket)))
+}
+
+async fn bridge(broker: Arc<SessionBroker>, tenant_id: Uuid, user_id: Uuid, id: Uuid, mut socket: WebSocket) {
+    let (mut local, proxied) = tokio::io::duplex(BRIDGE_BUFFER);
+    let attached = tokio::spawn(async move { broker.attach(tenant_id, user_id, id, proxied).await });
+    let mut buf = vec![0u8; BRIDGE_BUFFER];
+    loop {
+        tokio::select! {
+            message = socket.recv() => {
+                let data = match message {
+                    Some(Ok(Message::Binary(data))) => data,
+                    Some(Ok(Message::Text(text))) => text.into_bytes(),
+                    Some(Ok(Message::Ping(_) | Message::Pong(_))) => continue,
+                    _ => break,
+                };
+                if local.write_all(&data).await.is_err() {
+                    break;
+                }
+            }
+            read = local.read(&mut buf) => match read {
+                Ok(0) | Err(_) => break,
+                Ok(n) => {
+                    if socket.send(Message::Binary(buf[..n].to_vec())).await.is_err() {
+                        break;
+                    }
+                }
+            },
+        }
+    }
+    // Closing our end lets the proxy finish and save the recording
+    drop(local);
+    match attached.await {
+        Ok(Err(e)) => warn!("SSH session {} attach failed: {}", id, e),
+        Err(e) => warn!("SSH session {} proxy task failed: {}", id, e),
+        Ok(Ok(_)) => {}
+    }
+    let _ = socket.close().await;
+}
+
+#[axum::debug_handler(state = DbPool)]
+pub async fn ssh_ca_handler(
+    bastion: Option<Extension<Arc<SessionBroker>>>,
+    auth: AuthUser,
+) -> AppResult<Json<CaResponse>> {
+    auth.require(Scope::StartSessions)?;
+    let broker = broker(bastion)?;
+    Ok(Json(CaResponse { keys: broker.trusted_ca_keys().await?, trust_script: broker.trust_script().await? }))
+}
+
+#[axum::debug_handler(state = DbPool)]
+pub async fn list_sessions_handler(
+    bastion: Option<Extension<Arc<SessionBroker>>>,
+    auth: AuthUser,
+    Query(params): Query<ListSessionsParams>,
+) -> AppResult<Json<Vec<SshSession>>> {
+    auth.require(Scope::ManageTenants)?;
+    let filter = SessionFilter { tenant_id: params.tenant_id, status: params.status };
+    let sessions = broker(bastion)?.list(&filter, Utc::now(), params.limit.unwrap_or(DEFAULT_LIMIT)).await?;
+    Ok(Json(sessions))
+}
+
+#[axum::debug_handler]
+pub async fn terminate_session_handler(
+    State(db): State<DbPool>,
+    bastion: Option<Extension<Arc<SessionBroker>>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<SshSession>> {
+    auth.require(Scope::ManageTenants)?;
+    auth.require_direct("Terminating SSH sessions")?;
+    let session = broker(bastion)?.terminate(id, auth.actor_id(), Utc::now()).await?;
+    record_audit(
+        &db,
+        &auth,
+        "ssh.session_terminate",
+        Some(id),
+        json!({ "tenant_id": session.tenant_id, "user_id": session.user_id, "instance_id": session.instance_id }),
+    )
+    .await;
+    Ok(Json(session))
+}
+
+#[axum::debug_handler(state = DbPool)]
+pub async fn session_recording_handler(
+    bastion: Option<Extension<Arc<SessionBroker>>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<RecordingResponse>> {
+    auth.require(Scope::ManageTenants)?;
+    let broker = broker(bastion)?;
+    let replay = broker.replay(id).await?;
+    let session = broker.session(id, Utc::now()).await?;
+    Ok(Json(RecordingResponse { session, chunks: replay.chunks }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bastion::{InMemorySshSessionStore, InstanceDirectory};
+    use crate::middleware::{ApiKeyService, InMemoryApiKeyStore};
+    use axum::async_trait;
+    use axum::{body::Body, http::Request, routing::post, Router};
+    use sirsi_compute_manager::fleet::{Instance, InstanceState};
+    use sirsi_key_vault::secret::{
+        AccessPolicy, AccessPolicyManager, InMemoryAccessPolicyManager, InMemoryAuditLogger, SecretAction, SecretPermission,
+    };
+    use sirsi_key_vault::signing::SigningService;
+    use sirsi_key_vault::ssh::SshCertificateAuthority;
+    use sqlx::postgres::PgPoolOptions;
+    use std::collections::HashMap;
+    use tower::ServiceExt;
+
+    struct OneInstance;
+
+    #[async_trait]
+    impl InstanceDirectory for OneInstance {
+        async fn instance(&self, fleet_id: &str, group_id: &str, instance_id: &str) -> AppResult<Option<Instance>> {
+            Ok(Some(Instance {
+                instance_id: instance_id.into(),
+                group_id: group_id.into(),
+                fleet_id: fleet_id.into(),
+                instance_type: "t3.micro".into(),
+                private_ip: "10.0.0.1".into(),
+                public_ip: None,
+                state: InstanceState::Running,
+                launch_time: Utc::now(),
+                labels: HashMap::new(),
+                metrics: None,
+            }))
+        }
+    }
+
+    async fn session_broker() -> Arc<SessionBroker> {
+        let policies = InMemoryAccessPolicyManager::new();
+        policies
+            .create_policy(AccessPolicy {
+                id: "bastion".into(),
+                name: "bastion".into(),
+                description: None,
+                principals: vec!["svc-bastion".into()],
+                permissions: vec![SecretPermission {
+                    actions: vec![SecretAction::Read, SecretAction::Write, SecretAction::Sign],
+                    secret_patterns: vec!["signing/*".into()],
+                }],
+                conditions: None,
+            })
+            .await
+            .unwrap();
+        let signing = Arc::new(SigningService::new(Arc::new(policies), Arc::new(InMemoryAuditLogger::new())));
+        let ca = Arc::new(SshCertificateAuthority::new(signing, "svc-bastion"));
+        ca.ensure_key().await.unwrap();
+        Arc::new(SessionBroker::new(Arc::new(InMemorySshSessionStore::new()), ca, Arc::new(OneInstance)))
+    }
+
+    #[tokio::test]
+    async fn test_starting_sessions_requires_session_scope() {
+        let keys = Arc::new(ApiKeyService::new(Arc::new(InMemoryApiKeyStore::new())));
+        let owner = Uuid::new_v4();
+        let user_scopes = Scope::for_role("user");
+        let (_, user_key) = keys.issue(owner, owner, "ci", &user_scopes, None, Utc::now()).await.unwrap();
+        let (_, ops_key) = keys.issue(owner, owner, "ops", &[Scope::StartSessions], None, Utc::now()).await.unwrap();
+        let pool = PgPoolOptions::new()
+            .acquire_timeout(std::time::Duration::from_millis(100))
+            .connect_lazy("postgresql://root@localhost:26257/sirsi_test")
+            .unwrap();
+        let app = Router::new()
+            .route("/fleets/:fleet_id/groups/:group_id/instances/:instance_id/ssh-sessions", post(start_session_handler))
+            .layer(Extension(session_broker().await))
+            .layer(Extension(keys))
+            .with_state(pool);
+
+        let (operator_key, _) = crate::bastion::ephemeral_key().unwrap();
+        let start = |key: &str| {
+            Request::builder()
+                .method("POST")
+                .uri("/fleets/web/groups/blue/instances/i-1/ssh-sessions")
+                .header("Authorization", format!("ApiKey {}", key))
+                .header("Content-Type", "application/json")
+                .body(Body::from(json!({ "login": "ec2-user", "public_key": operator_key }).to_string()))
+                .unwrap()
+        };
+        let response = app.clone().oneshot(start(&user_key)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let response = app.oneshot(start(&ops_key)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let grant: serde_json::Value =
+            serde_json::from_slice(&hyper::body::to_bytes(response.into_body()).await.unwrap()).unwrap();
+        assert_eq!(grant["connection"]["type"], "direct");
+        assert_eq!(grant["session"]["tenant_id"], owner.to_string());
+    }
+}
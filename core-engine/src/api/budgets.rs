@@ -0,0 +1,138 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    budgets::{Budget, BudgetService, BudgetStatus, Enforcement, NewBudget},
+    db::DbPool,
+    error::{AppError, AppResult},
+    metering::ResourceScope,
+    middleware::{AuthUser, Scope},
+    models::project::Project,
+    orgs::{OrgPermission, OrgService},
+};
+
+use super::audit::record_audit;
+
+#[derive(Debug, Deserialize)]
+pub struct OverrideRequest {
+    pub reason: Option<String>,
+}
+
+#[axum::debug_handler]
+pub async fn create_budget_handler(
+    State(db): State<DbPool>,
+    Extension(budgets): Extension<Arc<BudgetService>>,
+    Extension(orgs): Extension<Arc<OrgService>>,
+    auth: AuthUser,
+    Json(budget): Json<NewBudget>,
+) -> AppResult<(StatusCode, Json<Budget>)> {
+    auth.require(Scope::WriteResources)?;
+    if let ResourceScope::Project { project_id } = &budget.scope {
+        let project = Project::find_by_id(&db, *project_id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Project not found".into()))?;
+        orgs.authorize_project(auth.user_id, (&project).into(), OrgPermission::ReadProjects).await?;
+    }
+    let budget = budgets.create(auth.user_id, budget, Utc::now()).await?;
+    record_audit(&db, &auth, "budget.create", Some(budget.id), json!({
+        "name": budget.name,
+        "scope": budget.scope,
+        "monthly_amount": budget.monthly_amount,
+        "enforcement": budget.enforcement,
+    })).await;
+    Ok((StatusCode::CREATED, Json(budget)))
+}
+
+#[axum::debug_handler(state = DbPool)]
+pub async fn list_budgets_handler(
+    Extension(budgets): Extension<Arc<BudgetService>>,
+    auth: AuthUser,
+) -> AppResult<Json<Vec<Budget>>> {
+    auth.require(Scope::ReadResources)?;
+    Ok(Json(budgets.budgets(auth.user_id).await?))
+}
+
+#[axum::debug_handler(state = DbPool)]
+pub async fn get_budget_handler(
+    Extension(budgets): Extension<Arc<BudgetService>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<Budget>> {
+    auth.require(Scope::ReadResources)?;
+    Ok(Json(budgets.budget(auth.user_id, id).await?))
+}
+
+#[axum::debug_handler]
+pub async fn delete_budget_handler(
+    State(db): State<DbPool>,
+    Extension(budgets): Extension<Arc<BudgetService>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> AppResult<StatusCode> {
+    auth.require(Scope::WriteResources)?;
+    budgets.delete(auth.user_id, id).await?;
+    record_audit(&db, &auth, "budget.delete", Some(id), json!({})).await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Month-to-date spend, the linear forecast, alerts sent and any enforcement this month
+#[axum::debug_handler(state = DbPool)]
+pub async fn get_budget_status_handler(
+    Extension(budgets): Extension<Arc<BudgetService>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<BudgetStatus>> {
+    auth.require(Scope::ReadResources)?;
+    Ok(Json(budgets.status(auth.user_id, id, Utc::now()).await?))
+}
+
+#[axum::debug_handler(state = DbPool)]
+pub async fn list_budget_enforcements_handler(
+    Extension(budgets): Extension<Arc<BudgetService>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<Vec<Enforcement>>> {
+    auth.require(Scope::ReadResources)?;
+    budgets.budget(auth.user_id, id).await?;
+    Ok(Json(budgets.enforcements(auth.user_id, Some(id)).await?))
+}
+
+#[axum::debug_handler(state = DbPool)]
+pub async fn admin_list_enforcements_handler(
+    Extension(budgets): Extension<Arc<BudgetService>>,
+    auth: AuthUser,
+    Path(tenant_id): Path<Uuid>,
+) -> AppResult<Json<Vec<Enforcement>>> {
+    auth.require(Scope::ManageTenants)?;
+    Ok(Json(budgets.enforcements(tenant_id, None).await?))
+}
+
+// Lifts the block or restarts the fleets; the budget is not enforced again this month
+#[axum::debug_handler]
+pub async fn override_enforcement_handler(
+    State(db): State<DbPool>,
+    Extension(budgets): Extension<Arc<BudgetService>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<OverrideRequest>,
+) -> AppResult<Json<Enforcement>> {
+    auth.require(Scope::ManageTenants)?;
+    auth.require_direct("Overriding budget enforcement")?;
+    let enforcement = budgets.override_enforcement(id, auth.user_id, payload.reason, Utc::now()).await?;
+    record_audit(&db, &auth, "budget.enforcement_override", Some(enforcement.budget_id), json!({
+        "enforcement_id": enforcement.id,
+        "tenant_id": enforcement.tenant_id,
+        "kind": enforcement.kind,
+        "reason": enforcement.override_reason,
+    })).await;
+    Ok(Json(enforcement))
+}
@@ -0,0 +1,178 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use serde::Deserialize;
+use serde_json::json;
+use sirsi_compute_manager::error::ComputeError;
+use sirsi_compute_manager::fleet::{CommandInvocation, CommandOptions, CommandRunner};
+
+use crate::{
+    db::DbPool,
+    error::{AppError, AppResult},
+    middleware::{AuthUser, Scope},
+};
+
+use super::audit::record_audit;
+
+#[derive(Debug, Deserialize)]
+pub struct RunCommandRequest {
+    pub command: String,
+    #[serde(default)]
+    pub options: CommandOptions,
+}
+
+fn compute_error(error: ComputeError) -> AppError {
+    match error {
+        ComputeError::NotFound(msg) => AppError::NotFound(msg),
+        ComputeError::Validation(msg) => AppError::Validation(msg),
+        other => AppError::Internal(other.to_string()),
+    }
+}
+
+fn runner(runner: Option<Extension<Arc<CommandRunner>>>) -> AppResult<Arc<CommandRunner>> {
+    runner
+        .map(|Extension(runner)| runner)
+        .ok_or_else(|| AppError::Configuration("Remote command execution is not configured".into()))
+}
+
+// Returns as soon as the targets are resolved; poll the invocation for per-instance results
+#[axum::debug_handler(state = DbPool)]
+pub async fn run_command_handler(
+    State(db): State<DbPool>,
+    commands: Option<Extension<Arc<CommandRunner>>>,
+    auth: AuthUser,
+    Path((fleet_id, group_id)): Path<(String, String)>,
+    Json(request): Json<RunCommandRequest>,
+) -> AppResult<(StatusCode, Json<CommandInvocation>)> {
+    auth.require(Scope::ExecuteCommands)?;
+    let runner = runner(commands)?;
+
+    let invocation = runner
+        .run_command(&fleet_id, &group_id, &request.command, request.options, &auth.user_id.to_string())
+        .await
+        .map_err(compute_error)?;
+
+    record_audit(
+        &db,
+        &auth,
+        "fleet.command",
+        None,
+        json!({
+            "invocation_id": invocation.id,
+            "fleet_id": fleet_id,
+            "group_id": group_id,
+            "command": invocation.command,
+            "targets": invocation.targets.len(),
+        }),
+    )
+    .await;
+    Ok((StatusCode::ACCEPTED, Json(invocation)))
+}
+
+#[axum::debug_handler(state = DbPool)]
+pub async fn get_command_handler(
+    commands: Option<Extension<Arc<CommandRunner>>>,
+    auth: AuthUser,
+    Path(id): Path<String>,
+) -> AppResult<Json<CommandInvocation>> {
+    auth.require(Scope::ExecuteCommands)?;
+    let invocation = runner(commands)?.get_invocation(&id).await.map_err(compute_error)?;
+    Ok(Json(invocation))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::{ApiKeyService, InMemoryApiKeyStore};
+    use axum::async_trait;
+    use axum::{body::Body, http::Request, routing::post, Router};
+    use chrono::Utc;
+    use sirsi_compute_manager::error::ComputeResult;
+    use sirsi_compute_manager::fleet::command::{CommandOutput, CommandPoll, RemoteCommand};
+    use sirsi_compute_manager::fleet::{
+        CommandBackend, FileOutputStore, InMemoryCommandInvocationStore, Instance, InstanceState,
+    };
+    use sqlx::postgres::PgPoolOptions;
+    use std::collections::HashMap;
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    struct EchoBackend;
+
+    #[async_trait]
+    impl CommandBackend for EchoBackend {
+        async fn list_instances(&self, fleet_id: &str, group_id: &str) -> ComputeResult<Vec<Instance>> {
+            Ok(vec![Instance {
+                instance_id: "i-1".into(),
+                group_id: group_id.into(),
+                fleet_id: fleet_id.into(),
+                instance_type: "t3.micro".into(),
+                private_ip: "10.0.0.1".into(),
+                public_ip: None,
+                state: InstanceState::Running,
+                launch_time: Utc::now(),
+                labels: HashMap::new(),
+                metrics: None,
+            }])
+        }
+
+        async fn start(&self, _instance: &Instance, _command: &RemoteCommand) -> ComputeResult<String> {
+            Ok("cmd-1".into())
+        }
+
+        async fn poll(&self, _instance: &Instance, _handle: &str) -> ComputeResult<CommandPoll> {
+            Ok(CommandPoll::Finished(CommandOutput { exit_code: 0, stdout: "ok".into(), stderr: String::new() }))
+        }
+
+        async fn cancel(&self, _instance: &Instance, _handle: &str) -> ComputeResult<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_running_commands_requires_execute_scope() {
+        let dir = tempfile::tempdir().unwrap();
+        let runner = Arc::new(CommandRunner::new(
+            Arc::new(EchoBackend),
+            Arc::new(InMemoryCommandInvocationStore::new()),
+            Arc::new(FileOutputStore::new(dir.path())),
+        ));
+        let keys = Arc::new(ApiKeyService::new(Arc::new(InMemoryApiKeyStore::new())));
+        let owner = Uuid::new_v4();
+        let user_scopes = Scope::for_role("user");
+        let (_, user_key) = keys.issue(owner, owner, "ci", &user_scopes, None, Utc::now()).await.unwrap();
+        let (_, ops_key) = keys.issue(owner, owner, "ops", &[Scope::ExecuteCommands], None, Utc::now()).await.unwrap();
+        let pool = PgPoolOptions::new()
+            .acquire_timeout(std::time::Duration::from_millis(100))
+            .connect_lazy("postgresql://root@localhost:26257/sirsi_test")
+            .unwrap();
+        let app = Router::new()
+            .route("/fleets/:fleet_id/groups/:group_id/commands", post(run_command_handler))
+            .layer(Extension(runner))
+            .layer(Extension(keys))
+            .with_state(pool);
+
+        let run = |key: &str| {
+            Request::builder()
+                .method("POST")
+                .uri("/fleets/web/groups/blue/commands")
+                .header("Authorization", format!("ApiKey {}", key))
+                .header("Content-Type", "application/json")
+                .body(Body::from(r#"{"command":"uptime","options":{"max_concurrency":2}}"#))
+                .unwrap()
+        };
+        let response = app.clone().oneshot(run(&user_key)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let response = app.oneshot(run(&ops_key)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        let invocation: CommandInvocation =
+            serde_json::from_slice(&hyper::body::to_bytes(response.into_body()).await.unwrap()).unwrap();
+        assert_eq!(invocation.targets.len(), 1);
+        assert_eq!(invocation.options.max_concurrency, 2);
+    }
+}
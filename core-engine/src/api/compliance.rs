@@ -0,0 +1,125 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{
+    compliance::{ComplianceRule, ComplianceScan, ComplianceService, Finding, FindingFilter, FindingStatus, NewComplianceRule, Severity},
+    db::DbPool,
+    error::AppResult,
+    middleware::{AuthUser, Scope},
+};
+
+use super::audit::record_audit;
+
+const DEFAULT_LIMIT: i64 = 100;
+const MAX_LIMIT: i64 = 1000;
+
+#[derive(Debug, Deserialize)]
+pub struct SetRuleEnabledRequest {
+    pub enabled: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FindingParams {
+    #[serde(default)]
+    pub status: FindingStatus,
+    pub rule: Option<String>,
+    pub severity: Option<Severity>,
+    pub limit: Option<i64>,
+}
+
+// The built-in pack followed by the tenant's own rules
+#[axum::debug_handler(state = DbPool)]
+pub async fn list_compliance_rules_handler(
+    Extension(compliance): Extension<Arc<ComplianceService>>,
+    auth: AuthUser,
+) -> AppResult<Json<Vec<ComplianceRule>>> {
+    auth.require(Scope::ReadResources)?;
+    Ok(Json(compliance.rules(auth.user_id).await?))
+}
+
+#[axum::debug_handler]
+pub async fn create_compliance_rule_handler(
+    State(db): State<DbPool>,
+    Extension(compliance): Extension<Arc<ComplianceService>>,
+    auth: AuthUser,
+    Json(rule): Json<NewComplianceRule>,
+) -> AppResult<(StatusCode, Json<ComplianceRule>)> {
+    auth.require(Scope::WriteResources)?;
+    let rule = compliance.create_rule(auth.user_id, rule).await?;
+    record_audit(&db, &auth, "compliance.rule_create", None, json!({
+        "key": rule.key,
+        "severity": rule.severity,
+        "expression": rule.expression,
+    })).await;
+    Ok((StatusCode::CREATED, Json(rule)))
+}
+
+#[axum::debug_handler]
+pub async fn set_compliance_rule_enabled_handler(
+    State(db): State<DbPool>,
+    Extension(compliance): Extension<Arc<ComplianceService>>,
+    auth: AuthUser,
+    Path(key): Path<String>,
+    Json(payload): Json<SetRuleEnabledRequest>,
+) -> AppResult<Json<ComplianceRule>> {
+    auth.require(Scope::WriteResources)?;
+    let rule = compliance.set_enabled(auth.user_id, &key, payload.enabled).await?;
+    record_audit(&db, &auth, "compliance.rule_toggle", None, json!({
+        "key": rule.key,
+        "enabled": rule.enabled,
+    })).await;
+    Ok(Json(rule))
+}
+
+#[axum::debug_handler]
+pub async fn delete_compliance_rule_handler(
+    State(db): State<DbPool>,
+    Extension(compliance): Extension<Arc<ComplianceService>>,
+    auth: AuthUser,
+    Path(key): Path<String>,
+) -> AppResult<StatusCode> {
+    auth.require(Scope::WriteResources)?;
+    compliance.delete_rule(auth.user_id, &key).await?;
+    record_audit(&db, &auth, "compliance.rule_delete", None, json!({ "key": key })).await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[axum::debug_handler(state = DbPool)]
+pub async fn list_compliance_findings_handler(
+    Extension(compliance): Extension<Arc<ComplianceService>>,
+    auth: AuthUser,
+    Query(params): Query<FindingParams>,
+) -> AppResult<Json<Vec<Finding>>> {
+    auth.require(Scope::ReadResources)?;
+    let filter = FindingFilter { status: params.status, rule_key: params.rule, severity: params.severity };
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    Ok(Json(compliance.findings(auth.user_id, &filter, limit).await?))
+}
+
+// Scans now rather than waiting for the next discovery run
+#[axum::debug_handler(state = DbPool)]
+pub async fn run_compliance_scan_handler(
+    Extension(compliance): Extension<Arc<ComplianceService>>,
+    auth: AuthUser,
+) -> AppResult<(StatusCode, Json<ComplianceScan>)> {
+    auth.require(Scope::WriteResources)?;
+    Ok((StatusCode::CREATED, Json(compliance.scan(auth.user_id, Utc::now()).await?)))
+}
+
+// Scores per provider and project from the latest scan
+#[axum::debug_handler(state = DbPool)]
+pub async fn compliance_summary_handler(
+    Extension(compliance): Extension<Arc<ComplianceService>>,
+    auth: AuthUser,
+) -> AppResult<Json<ComplianceScan>> {
+    auth.require(Scope::ReadResources)?;
+    Ok(Json(compliance.summary(auth.user_id).await?))
+}
@@ -0,0 +1,100 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query},
+    http::StatusCode,
+    Extension, Json,
+};
+use chrono::Utc;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    db::DbPool,
+    discovery::{DiscoveryRun, DiscoveryService, NewDiscoveryRun, RunDiff},
+    entitlements::{EntitlementService, Feature},
+    error::{AppError, AppResult},
+    metering::{MeteringService, UsageEvent},
+    middleware::{AuthUser, Scope},
+};
+
+const DEFAULT_LIMIT: i64 = 50;
+const MAX_LIMIT: i64 = 500;
+
+#[derive(Debug, Deserialize)]
+pub struct ListRunsParams {
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DiffParams {
+    // `previous` (the default) or the id of the run to compare against
+    pub against: Option<String>,
+}
+
+// Agents report a completed discovery here so later runs can be compared with it
+#[axum::debug_handler(state = DbPool)]
+pub async fn record_discovery_run_handler(
+    Extension(discovery): Extension<Arc<DiscoveryService>>,
+    Extension(metering): Extension<Arc<MeteringService>>,
+    Extension(entitlements): Extension<Arc<EntitlementService>>,
+    auth: AuthUser,
+    Json(run): Json<NewDiscoveryRun>,
+) -> AppResult<(StatusCode, Json<DiscoveryRun>)> {
+    auth.require(Scope::WriteResources)?;
+    // The first run from an account connects it, which counts against the plan's cloud accounts
+    let account = format!("{}/{}", run.provider, run.account_id);
+    let counted = entitlements.acquire(auth.user_id, Feature::CloudAccounts, &account).await?;
+    let now = Utc::now();
+    let run = match discovery.record_run(auth.user_id, run, now).await {
+        Ok(run) => run,
+        Err(e) => {
+            if counted {
+                entitlements.release(auth.user_id, Feature::CloudAccounts, &account).await?;
+            }
+            return Err(e);
+        }
+    };
+    metering.record(auth.user_id, UsageEvent::DiscoveryRun, now).await;
+    Ok((StatusCode::CREATED, Json(run)))
+}
+
+#[axum::debug_handler(state = DbPool)]
+pub async fn list_discovery_runs_handler(
+    Extension(discovery): Extension<Arc<DiscoveryService>>,
+    auth: AuthUser,
+    Query(params): Query<ListRunsParams>,
+) -> AppResult<Json<Vec<DiscoveryRun>>> {
+    auth.require(Scope::ReadResources)?;
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    Ok(Json(discovery.runs(auth.user_id, limit).await?))
+}
+
+#[axum::debug_handler(state = DbPool)]
+pub async fn get_discovery_run_handler(
+    Extension(discovery): Extension<Arc<DiscoveryService>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<DiscoveryRun>> {
+    auth.require(Scope::ReadResources)?;
+    Ok(Json(discovery.run(auth.user_id, id).await?))
+}
+
+#[axum::debug_handler(state = DbPool)]
+pub async fn diff_discovery_run_handler(
+    Extension(discovery): Extension<Arc<DiscoveryService>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+    Query(params): Query<DiffParams>,
+) -> AppResult<Json<RunDiff>> {
+    auth.require(Scope::ReadResources)?;
+    let diff = match params.against.as_deref() {
+        None | Some("previous") => discovery.diff_against_previous(auth.user_id, id).await?,
+        Some(base) => {
+            let base = Uuid::parse_str(base)
+                .map_err(|_| AppError::InvalidInput("against must be `previous` or a run id".into()))?;
+            discovery.diff_runs(auth.user_id, base, id).await?
+        }
+    };
+    Ok(Json(diff))
+}
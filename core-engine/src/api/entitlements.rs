@@ -0,0 +1,83 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    Extension, Json,
+};
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    db::DbPool,
+    entitlements::{Entitlement, EntitlementService, Feature, Plan, PlanTier, TenantEntitlements, TenantPlan},
+    error::AppResult,
+    middleware::{AuthUser, Scope},
+};
+
+use super::audit::record_audit;
+
+#[derive(Debug, Deserialize)]
+pub struct SetPlanRequest {
+    pub tier: PlanTier,
+    #[serde(default)]
+    pub overrides: BTreeMap<Feature, Entitlement>,
+}
+
+#[axum::debug_handler(state = DbPool)]
+pub async fn list_plans_handler(
+    Extension(entitlements): Extension<Arc<EntitlementService>>,
+    _auth: AuthUser,
+) -> AppResult<Json<Vec<Plan>>> {
+    Ok(Json(entitlements.catalog().plans().to_vec()))
+}
+
+// The caller's plan, what it allows and how much of each limit is in use
+#[axum::debug_handler(state = DbPool)]
+pub async fn get_entitlements_handler(
+    Extension(entitlements): Extension<Arc<EntitlementService>>,
+    auth: AuthUser,
+) -> AppResult<Json<TenantEntitlements>> {
+    Ok(Json(entitlements.entitlements(auth.user_id).await?))
+}
+
+#[axum::debug_handler(state = DbPool)]
+pub async fn get_tenant_plan_handler(
+    Extension(entitlements): Extension<Arc<EntitlementService>>,
+    auth: AuthUser,
+    Path(tenant_id): Path<Uuid>,
+) -> AppResult<Json<TenantPlan>> {
+    auth.require(Scope::ManageTenants)?;
+    Ok(Json(entitlements.tenant_plan(tenant_id).await?))
+}
+
+// Takes effect on the tenant's next request. Units already held above a lowered limit are kept;
+// only new ones are refused.
+#[axum::debug_handler]
+pub async fn set_tenant_plan_handler(
+    State(db): State<DbPool>,
+    Extension(entitlements): Extension<Arc<EntitlementService>>,
+    auth: AuthUser,
+    Path(tenant_id): Path<Uuid>,
+    Json(request): Json<SetPlanRequest>,
+) -> AppResult<Json<TenantPlan>> {
+    auth.require(Scope::ManageTenants)?;
+    let previous = entitlements.tenant_plan(tenant_id).await?;
+    let plan = entitlements
+        .set_plan(tenant_id, request.tier, request.overrides, auth.user_id, Utc::now())
+        .await?;
+    record_audit(
+        &db,
+        &auth,
+        "tenant.plan",
+        Some(tenant_id),
+        json!({
+            "from": { "tier": previous.tier, "overrides": previous.overrides },
+            "to": { "tier": plan.tier, "overrides": plan.overrides },
+        }),
+    )
+    .await;
+    Ok(Json(plan))
+}
@@ -0,0 +1,790 @@
+use std::collections::HashMap;
+use std::path::Path as FsPath;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{
+    async_trait,
+    body::{Bytes, StreamBody},
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use futures::stream::{self, BoxStream, StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sirsi_object_store::{ObjectStore, PresignMethod, PutOptions, Uploader};
+use sqlx::FromRow;
+use time::OffsetDateTime;
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::{
+    db::DbPool,
+    error::{AppError, AppResult},
+    middleware::{AuthUser, Scope},
+    models::{AuditEvent, AuditFilter, Resource, ResourceFilter},
+};
+
+// Larger exports are written to object storage in the background instead of streamed
+const DEFAULT_INLINE_LIMIT: u64 = 50_000;
+const DEFAULT_PAGE_SIZE: i64 = 500;
+const DEFAULT_LINK_TTL: Duration = Duration::from_secs(15 * 60);
+
+pub const RESOURCE_COLUMNS: &[&str] =
+    &["id", "name", "resource_type", "project_id", "owner_id", "data", "created_at", "updated_at"];
+pub const AUDIT_COLUMNS: &[&str] =
+    &["id", "created_at", "actor_id", "action", "target_type", "target_id", "details"];
+
+pub type ExportRecord = Map<String, Value>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "VARCHAR")]
+pub enum ExportFormat {
+    #[serde(rename = "csv")]
+    #[sqlx(rename = "csv")]
+    Csv,
+    #[serde(rename = "jsonl")]
+    #[sqlx(rename = "jsonl")]
+    JsonLines,
+}
+
+impl ExportFormat {
+    // An explicit `format` parameter wins over the Accept header; CSV is the default
+    pub fn negotiate(param: Option<&str>, accept: Option<&str>) -> AppResult<Self> {
+        if let Some(param) = param {
+            return match param.to_ascii_lowercase().as_str() {
+                "csv" => Ok(Self::Csv),
+                "jsonl" | "ndjson" | "json" => Ok(Self::JsonLines),
+                other => Err(AppError::InvalidInput(format!("Unsupported export format '{}'", other))),
+            };
+        }
+        let media_types = accept.unwrap_or_default().split(',').map(|m| m.split(';').next().unwrap_or_default().trim());
+        for media_type in media_types {
+            match media_type {
+                "text/csv" => return Ok(Self::Csv),
+                "application/x-ndjson" | "application/jsonl" | "application/json" => return Ok(Self::JsonLines),
+                _ => {}
+            }
+        }
+        Ok(Self::Csv)
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Csv => "text/csv; charset=utf-8",
+            Self::JsonLines => "application/x-ndjson",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::JsonLines => "jsonl",
+        }
+    }
+}
+
+// Columns come out in `available` order whatever order they were requested in
+pub fn select_columns(available: &[&'static str], requested: Option<&str>) -> AppResult<Vec<&'static str>> {
+    let wanted: Vec<&str> = requested.unwrap_or_default().split(',').map(str::trim).filter(|c| !c.is_empty()).collect();
+    if wanted.is_empty() {
+        return Ok(available.to_vec());
+    }
+    if let Some(unknown) = wanted.iter().find(|c| !available.contains(c)) {
+        return Err(AppError::InvalidInput(format!(
+            "Unknown export column '{}', expected one of: {}",
+            unknown,
+            available.join(", ")
+        )));
+    }
+    Ok(available.iter().copied().filter(|c| wanted.contains(c)).collect())
+}
+
+// RFC 4180: fields holding a delimiter, quote or line break are quoted, with quotes doubled
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn csv_value(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(s)) => csv_field(s),
+        Some(other) => csv_field(&other.to_string()),
+    }
+}
+
+pub fn encode_header(format: ExportFormat, columns: &[&str]) -> Option<String> {
+    match format {
+        ExportFormat::Csv => Some(format!("{}\r\n", columns.iter().map(|c| csv_field(c)).collect::<Vec<_>>().join(","))),
+        ExportFormat::JsonLines => None,
+    }
+}
+
+pub fn encode_record(format: ExportFormat, columns: &[&str], record: &ExportRecord) -> String {
+    match format {
+        ExportFormat::Csv => {
+            format!("{}\r\n", columns.iter().map(|c| csv_value(record.get(*c))).collect::<Vec<_>>().join(","))
+        }
+        ExportFormat::JsonLines => {
+            // Built by hand so keys keep the column order
+            let fields: Vec<String> = columns
+                .iter()
+                .map(|c| format!("{}:{}", Value::from(*c), record.get(*c).unwrap_or(&Value::Null)))
+                .collect();
+            format!("{{{}}}\n", fields.join(","))
+        }
+    }
+}
+
+fn timestamp(value: Option<OffsetDateTime>) -> Value {
+    value
+        .and_then(|t| DateTime::<Utc>::from_timestamp(t.unix_timestamp(), t.nanosecond()))
+        .map(|t| Value::String(t.to_rfc3339()))
+        .unwrap_or(Value::Null)
+}
+
+pub fn resource_record(resource: &Resource) -> ExportRecord {
+    let mut record = Map::new();
+    record.insert("id".into(), resource.id.to_string().into());
+    record.insert("name".into(), resource.name.clone().into());
+    record.insert("resource_type".into(), resource.resource_type.clone().into());
+    record.insert("project_id".into(), resource.project_id.to_string().into());
+    record.insert("owner_id".into(), resource.owner_id.to_string().into());
+    record.insert("data".into(), resource.data.clone());
+    record.insert("created_at".into(), timestamp(resource.created_at));
+    record.insert("updated_at".into(), timestamp(resource.updated_at));
+    record
+}
+
+pub fn audit_record(event: &AuditEvent) -> ExportRecord {
+    let mut record = Map::new();
+    record.insert("id".into(), event.id.to_string().into());
+    record.insert("created_at".into(), timestamp(event.created_at));
+    record.insert("actor_id".into(), event.actor_id.to_string().into());
+    record.insert("action".into(), event.action.clone().into());
+    record.insert("target_type".into(), event.target_type.clone().into());
+    record.insert("target_id".into(), event.target_id.map(|id| id.to_string()).into());
+    record.insert("details".into(), event.details.clone());
+    record
+}
+
+// A tenant-scoped, filtered set of rows that can be counted and walked page by page
+#[async_trait]
+pub trait ExportSource: Send + Sync {
+    fn kind(&self) -> &'static str;
+    async fn count(&self) -> AppResult<u64>;
+    // Rows after the `after` cursor in id order, paired with their ids
+    async fn page(&self, after: Option<Uuid>, limit: i64) -> AppResult<Vec<(Uuid, ExportRecord)>>;
+}
+
+pub struct ResourceSource {
+    db: DbPool,
+    owner_id: Uuid,
+    filter: ResourceFilter,
+}
+
+impl ResourceSource {
+    pub fn new(db: DbPool, owner_id: Uuid, filter: ResourceFilter) -> Self {
+        Self { db, owner_id, filter }
+    }
+}
+
+#[async_trait]
+impl ExportSource for ResourceSource {
+    fn kind(&self) -> &'static str {
+        "resources"
+    }
+
+    async fn count(&self) -> AppResult<u64> {
+        Ok(Resource::count_filtered(&self.db, self.owner_id, &self.filter).await?.max(0) as u64)
+    }
+
+    async fn page(&self, after: Option<Uuid>, limit: i64) -> AppResult<Vec<(Uuid, ExportRecord)>> {
+        let resources = Resource::page_after(&self.db, self.owner_id, &self.filter, after, limit).await?;
+        Ok(resources.iter().map(|r| (r.id, resource_record(r))).collect())
+    }
+}
+
+pub struct AuditSource {
+    db: DbPool,
+    owner_id: Uuid,
+    filter: AuditFilter,
+}
+
+impl AuditSource {
+    pub fn new(db: DbPool, owner_id: Uuid, filter: AuditFilter) -> Self {
+        Self { db, owner_id, filter }
+    }
+}
+
+#[async_trait]
+impl ExportSource for AuditSource {
+    fn kind(&self) -> &'static str {
+        "audit"
+    }
+
+    async fn count(&self) -> AppResult<u64> {
+        Ok(AuditEvent::count(&self.db, self.owner_id, &self.filter).await?.max(0) as u64)
+    }
+
+    async fn page(&self, after: Option<Uuid>, limit: i64) -> AppResult<Vec<(Uuid, ExportRecord)>> {
+        let events = AuditEvent::page_after(&self.db, self.owner_id, &self.filter, after, limit).await?;
+        Ok(events.iter().map(|e| (e.id, audit_record(e))).collect())
+    }
+}
+
+// Only one page is held in memory at a time
+fn record_stream(source: Arc<dyn ExportSource>, page_size: i64) -> BoxStream<'static, AppResult<ExportRecord>> {
+    stream::try_unfold((source, None, false), move |(source, after, done)| async move {
+        if done {
+            return Ok::<_, AppError>(None);
+        }
+        let page = source.page(after, page_size).await?;
+        let done = (page.len() as i64) < page_size;
+        let after = page.last().map(|(id, _)| *id).or(after);
+        Ok(Some((page, (source, after, done))))
+    })
+    .map_ok(|page| stream::iter(page.into_iter().map(|(_, record)| Ok(record))))
+    .try_flatten()
+    .boxed()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "VARCHAR", rename_all = "lowercase")]
+pub enum ExportStatus {
+    Pending,
+    Running,
+    Completed,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct ExportJob {
+    pub id: Uuid,
+    pub owner_id: Uuid,
+    pub kind: String,
+    pub format: ExportFormat,
+    pub status: ExportStatus,
+    #[serde(skip_serializing)]
+    pub object_key: Option<String>,
+    pub row_count: Option<i64>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExportJobResponse {
+    #[serde(flatten)]
+    pub job: ExportJob,
+    // Presigned link, minted fresh on every poll of a completed job
+    pub download_url: Option<String>,
+}
+
+#[async_trait]
+pub trait ExportJobStore: Send + Sync {
+    async fn create(&self, job: &ExportJob) -> AppResult<()>;
+    async fn update(&self, job: &ExportJob) -> AppResult<()>;
+    async fn get(&self, owner_id: Uuid, id: Uuid) -> AppResult<Option<ExportJob>>;
+}
+
+pub struct PgExportJobStore {
+    db: DbPool,
+}
+
+impl PgExportJobStore {
+    pub fn new(db: DbPool) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl ExportJobStore for PgExportJobStore {
+    async fn create(&self, job: &ExportJob) -> AppResult<()> {
+        sqlx::query(
+            r#"INSERT INTO export_jobs (id, owner_id, kind, format, status, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6)"#,
+        )
+        .bind(job.id)
+        .bind(job.owner_id)
+        .bind(&job.kind)
+        .bind(job.format)
+        .bind(job.status)
+        .bind(job.created_at)
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+
+    async fn update(&self, job: &ExportJob) -> AppResult<()> {
+        sqlx::query(
+            r#"UPDATE export_jobs
+            SET status = $1, object_key = $2, row_count = $3, error = $4, completed_at = $5
+            WHERE id = $6 AND owner_id = $7"#,
+        )
+        .bind(job.status)
+        .bind(&job.object_key)
+        .bind(job.row_count)
+        .bind(&job.error)
+        .bind(job.completed_at)
+        .bind(job.id)
+        .bind(job.owner_id)
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+
+    async fn get(&self, owner_id: Uuid, id: Uuid) -> AppResult<Option<ExportJob>> {
+        let job = sqlx::query_as::<_, ExportJob>(
+            r#"SELECT id, owner_id, kind, format, status, object_key, row_count, error, created_at, completed_at
+            FROM export_jobs WHERE id = $1 AND owner_id = $2"#,
+        )
+        .bind(id)
+        .bind(owner_id)
+        .fetch_optional(&self.db)
+        .await?;
+        Ok(job)
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryExportJobStore {
+    jobs: Mutex<HashMap<Uuid, ExportJob>>,
+}
+
+impl InMemoryExportJobStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl ExportJobStore for InMemoryExportJobStore {
+    async fn create(&self, job: &ExportJob) -> AppResult<()> {
+        self.jobs.lock().await.insert(job.id, job.clone());
+        Ok(())
+    }
+
+    async fn update(&self, job: &ExportJob) -> AppResult<()> {
+        self.jobs.lock().await.insert(job.id, job.clone());
+        Ok(())
+    }
+
+    async fn get(&self, owner_id: Uuid, id: Uuid) -> AppResult<Option<ExportJob>> {
+        Ok(self.jobs.lock().await.get(&id).filter(|j| j.owner_id == owner_id).cloned())
+    }
+}
+
+#[async_trait]
+pub trait ExportStorage: Send + Sync {
+    async fn upload(&self, key: &str, path: &FsPath, content_type: &str) -> AppResult<()>;
+    async fn presigned_url(&self, key: &str, expires_in: Duration) -> AppResult<String>;
+}
+
+// Exports land in whichever object store the deployment is configured with; large files go up
+// in resumable, checksummed parts
+pub struct ObjectExportStorage {
+    store: Arc<dyn ObjectStore>,
+}
+
+impl ObjectExportStorage {
+    pub fn new(store: Arc<dyn ObjectStore>) -> Self {
+        Self { store }
+    }
+}
+
+#[async_trait]
+impl ExportStorage for ObjectExportStorage {
+    async fn upload(&self, key: &str, path: &FsPath, content_type: &str) -> AppResult<()> {
+        Uploader::new(self.store.clone())
+            .upload_file(key, path, &PutOptions::new().with_content_type(content_type))
+            .await
+            .map_err(AppError::from_provider)?;
+        Ok(())
+    }
+
+    async fn presigned_url(&self, key: &str, expires_in: Duration) -> AppResult<String> {
+        self.store
+            .presign(key, PresignMethod::Get, expires_in)
+            .await
+            .map_err(AppError::from_provider)
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryExportStorage {
+    objects: Mutex<HashMap<String, Vec<u8>>>,
+}
+
+impl InMemoryExportStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub async fn object(&self, key: &str) -> Option<Vec<u8>> {
+        self.objects.lock().await.get(key).cloned()
+    }
+}
+
+#[async_trait]
+impl ExportStorage for InMemoryExportStorage {
+    async fn upload(&self, key: &str, path: &FsPath, _content_type: &str) -> AppResult<()> {
+        let contents = tokio::fs::read(path)
+            .await
+            .map_err(|e| AppError::Internal(format!("Failed to read export file: {}", e)))?;
+        self.objects.lock().await.insert(key.to_string(), contents);
+        Ok(())
+    }
+
+    async fn presigned_url(&self, key: &str, expires_in: Duration) -> AppResult<String> {
+        Ok(format!("memory://{}?expires_in={}", key, expires_in.as_secs()))
+    }
+}
+
+pub struct ExportService {
+    jobs: Arc<dyn ExportJobStore>,
+    // Without storage, exports over the inline limit are rejected
+    storage: Option<Arc<dyn ExportStorage>>,
+    inline_limit: u64,
+    page_size: i64,
+    link_ttl: Duration,
+}
+
+impl ExportService {
+    pub fn new(jobs: Arc<dyn ExportJobStore>) -> Self {
+        Self {
+            jobs,
+            storage: None,
+            inline_limit: DEFAULT_INLINE_LIMIT,
+            page_size: DEFAULT_PAGE_SIZE,
+            link_ttl: DEFAULT_LINK_TTL,
+        }
+    }
+
+    pub fn with_storage(mut self, storage: Arc<dyn ExportStorage>) -> Self {
+        self.storage = Some(storage);
+        self
+    }
+
+    pub fn with_inline_limit(mut self, rows: u64) -> Self {
+        self.inline_limit = rows;
+        self
+    }
+
+    pub fn with_page_size(mut self, rows: i64) -> Self {
+        self.page_size = rows.max(1);
+        self
+    }
+
+    pub fn with_link_ttl(mut self, ttl: Duration) -> Self {
+        self.link_ttl = ttl;
+        self
+    }
+
+    // Streams the export, or queues a background job and answers 202 when it's too large
+    pub async fn export(
+        &self,
+        owner_id: Uuid,
+        source: Arc<dyn ExportSource>,
+        format: ExportFormat,
+        columns: Vec<&'static str>,
+    ) -> AppResult<Response> {
+        let rows = source.count().await?;
+        if rows > self.inline_limit {
+            let job = self.queue(owner_id, source, format, columns).await?;
+            let location = format!("/exports/{}", job.id);
+            let response = ExportJobResponse { job, download_url: None };
+            return Ok((StatusCode::ACCEPTED, [(header::LOCATION, location)], Json(response)).into_response());
+        }
+
+        let header_row = encode_header(format, &columns).map(|h| Ok(Bytes::from(h)));
+        let body = stream::iter(header_row).chain(
+            record_stream(source.clone(), self.page_size)
+                .map_ok(move |record| Bytes::from(encode_record(format, &columns, &record))),
+        );
+        let disposition = format!("attachment; filename=\"{}-export.{}\"", source.kind(), format.extension());
+        Ok((
+            [(header::CONTENT_TYPE, format.content_type().to_string()), (header::CONTENT_DISPOSITION, disposition)],
+            StreamBody::new(body),
+        )
+            .into_response())
+    }
+
+    async fn queue(
+        &self,
+        owner_id: Uuid,
+        source: Arc<dyn ExportSource>,
+        format: ExportFormat,
+        columns: Vec<&'static str>,
+    ) -> AppResult<ExportJob> {
+        if self.storage.is_none() {
+            return Err(AppError::InvalidInput(format!(
+                "Export exceeds {} rows and background exports are not configured; narrow the filters",
+                self.inline_limit
+            )));
+        }
+        let job = ExportJob {
+            id: Uuid::new_v4(),
+            owner_id,
+            kind: source.kind().to_string(),
+            format,
+            status: ExportStatus::Pending,
+            object_key: None,
+            row_count: None,
+            error: None,
+            created_at: Utc::now(),
+            completed_at: None,
+        };
+        self.jobs.create(&job).await?;
+        info!("Queued {} export {} for {}", job.kind, job.id, owner_id);
+        self.spawn_job(job.clone(), source, columns);
+        Ok(job)
+    }
+
+    pub fn spawn_job(&self, mut job: ExportJob, source: Arc<dyn ExportSource>, columns: Vec<&'static str>) -> JoinHandle<()> {
+        let jobs = self.jobs.clone();
+        let storage = self.storage.clone();
+        let page_size = self.page_size;
+        tokio::spawn(async move {
+            job.status = ExportStatus::Running;
+            if let Err(e) = jobs.update(&job).await {
+                warn!("Failed to mark export {} running: {}", job.id, e);
+            }
+            let result = match storage {
+                Some(storage) => write_export(storage.as_ref(), &job, source, &columns, page_size).await,
+                None => Err(AppError::Configuration("No export storage configured".to_string())),
+            };
+            match result {
+                Ok((key, rows)) => {
+                    job.status = ExportStatus::Completed;
+                    job.object_key = Some(key);
+                    job.row_count = Some(rows);
+                }
+                Err(e) => {
+                    error!("Export {} failed: {}", job.id, e);
+                    job.status = ExportStatus::Failed;
+                    job.error = Some(e.to_string());
+                }
+            }
+            job.completed_at = Some(Utc::now());
+            if let Err(e) = jobs.update(&job).await {
+                error!("Failed to record result of export {}: {}", job.id, e);
+            }
+        })
+    }
+
+    pub async fn job_status(&self, owner_id: Uuid, id: Uuid) -> AppResult<ExportJobResponse> {
+        let job = self
+            .jobs
+            .get(owner_id, id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Export job not found".into()))?;
+        let download_url = match (&self.storage, job.status, &job.object_key) {
+            (Some(storage), ExportStatus::Completed, Some(key)) => Some(storage.presigned_url(key, self.link_ttl).await?),
+            _ => None,
+        };
+        Ok(ExportJobResponse { job, download_url })
+    }
+}
+
+// Spools to a temp file so the upload never needs the whole export in memory
+async fn write_export(
+    storage: &dyn ExportStorage,
+    job: &ExportJob,
+    source: Arc<dyn ExportSource>,
+    columns: &[&'static str],
+    page_size: i64,
+) -> AppResult<(String, i64)> {
+    let io_error = |e: std::io::Error| AppError::Internal(format!("Failed to write export file: {}", e));
+    let extension = job.format.extension();
+    let path = std::env::temp_dir().join(format!("sirsi-export-{}.{}", job.id, extension));
+    let result = async {
+        let mut writer = BufWriter::new(tokio::fs::File::create(&path).await.map_err(io_error)?);
+        if let Some(header_row) = encode_header(job.format, columns) {
+            writer.write_all(header_row.as_bytes()).await.map_err(io_error)?;
+        }
+        let mut records = record_stream(source, page_size);
+        let mut rows = 0;
+        while let Some(record) = records.try_next().await? {
+            writer.write_all(encode_record(job.format, columns, &record).as_bytes()).await.map_err(io_error)?;
+            rows += 1;
+        }
+        writer.flush().await.map_err(io_error)?;
+        let key = format!("exports/{}/{}.{}", job.owner_id, job.id, extension);
+        storage.upload(&key, &path, job.format.content_type()).await?;
+        Ok((key, rows))
+    }
+    .await;
+    if let Err(e) = tokio::fs::remove_file(&path).await {
+        warn!("Failed to remove export file {}: {}", path.display(), e);
+    }
+    result
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExportParams {
+    pub format: Option<String>,
+    pub columns: Option<String>,
+}
+
+fn accept(headers: &HeaderMap) -> Option<&str> {
+    headers.get(header::ACCEPT).and_then(|value| value.to_str().ok())
+}
+
+#[axum::debug_handler]
+pub async fn export_resources_handler(
+    State(db): State<DbPool>,
+    Extension(exports): Extension<Arc<ExportService>>,
+    auth: AuthUser,
+    headers: HeaderMap,
+    Query(filter): Query<ResourceFilter>,
+    Query(params): Query<ExportParams>,
+) -> AppResult<Response> {
+    auth.require(Scope::ReadResources)?;
+    let format = ExportFormat::negotiate(params.format.as_deref(), accept(&headers))?;
+    let columns = select_columns(RESOURCE_COLUMNS, params.columns.as_deref())?;
+    let source = Arc::new(ResourceSource::new(db, auth.user_id, filter));
+    exports.export(auth.user_id, source, format, columns).await
+}
+
+#[axum::debug_handler]
+pub async fn export_audit_handler(
+    State(db): State<DbPool>,
+    Extension(exports): Extension<Arc<ExportService>>,
+    auth: AuthUser,
+    headers: HeaderMap,
+    Query(filter): Query<AuditFilter>,
+    Query(params): Query<ExportParams>,
+) -> AppResult<Response> {
+    auth.require(Scope::ReadAudit)?;
+    let format = ExportFormat::negotiate(params.format.as_deref(), accept(&headers))?;
+    let columns = select_columns(AUDIT_COLUMNS, params.columns.as_deref())?;
+    let source = Arc::new(AuditSource::new(db, auth.user_id, filter));
+    exports.export(auth.user_id, source, format, columns).await
+}
+
+#[axum::debug_handler(state = DbPool)]
+pub async fn get_export_job_handler(
+    Extension(exports): Extension<Arc<ExportService>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<ExportJobResponse>> {
+    Ok(Json(exports.job_status(auth.user_id, id).await?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    struct VecSource(Vec<(Uuid, ExportRecord)>);
+
+    #[async_trait]
+    impl ExportSource for VecSource {
+        fn kind(&self) -> &'static str {
+            "resources"
+        }
+
+        async fn count(&self) -> AppResult<u64> {
+            Ok(self.0.len() as u64)
+        }
+
+        async fn page(&self, after: Option<Uuid>, limit: i64) -> AppResult<Vec<(Uuid, ExportRecord)>> {
+            let mut rows: Vec<_> = self.0.iter().filter(|(id, _)| after.is_none_or(|a| *id > a)).cloned().collect();
+            rows.sort_by_key(|(id, _)| *id);
+            rows.truncate(limit as usize);
+            Ok(rows)
+        }
+    }
+
+    fn record(name: &str, data: Value) -> (Uuid, ExportRecord) {
+        let id = Uuid::new_v4();
+        let mut record = Map::new();
+        record.insert("id".into(), id.to_string().into());
+        record.insert("name".into(), name.into());
+        record.insert("resource_type".into(), "database".into());
+        record.insert("data".into(), data);
+        (id, record)
+    }
+
+    #[test]
+    fn test_csv_escapes_commas_quotes_and_newlines() {
+        let columns = select_columns(RESOURCE_COLUMNS, Some("data, name ,id")).unwrap();
+        assert_eq!(columns, vec!["id", "name", "data"]);
+        assert!(select_columns(RESOURCE_COLUMNS, Some("name,password")).is_err());
+
+        let (id, record) = record("db, \"primary\"\nus-east", json!({"tier": "gold"}));
+        assert_eq!(encode_header(ExportFormat::Csv, &columns).unwrap(), "id,name,data\r\n");
+        assert_eq!(
+            encode_record(ExportFormat::Csv, &columns, &record),
+            format!("{},\"db, \"\"primary\"\"\nus-east\",\"{{\"\"tier\"\":\"\"gold\"\"}}\"\r\n", id)
+        );
+        // Columns missing from the record come out empty
+        assert_eq!(encode_record(ExportFormat::Csv, &["id", "updated_at"], &record), format!("{},\r\n", id));
+
+        let line = encode_record(ExportFormat::JsonLines, &columns, &record);
+        assert!(line.ends_with('\n') && !line.trim_end().contains('\n'));
+        assert!(line.starts_with(&format!("{{\"id\":\"{}\",\"name\":", id)));
+        assert_eq!(serde_json::from_str::<Value>(&line).unwrap()["name"], "db, \"primary\"\nus-east");
+
+        assert_eq!(ExportFormat::negotiate(None, Some("application/x-ndjson, text/csv;q=0.5")).unwrap(), ExportFormat::JsonLines);
+        assert_eq!(ExportFormat::negotiate(Some("csv"), Some("application/json")).unwrap(), ExportFormat::Csv);
+        assert_eq!(ExportFormat::negotiate(None, None).unwrap(), ExportFormat::Csv);
+    }
+
+    #[tokio::test]
+    async fn test_large_export_is_handed_off_to_background_job() {
+        let owner_id = Uuid::new_v4();
+        let source = Arc::new(VecSource((0..5).map(|i| record(&format!("db-{}", i), json!({}))).collect()));
+        let storage = Arc::new(InMemoryExportStorage::new());
+        let service = ExportService::new(Arc::new(InMemoryExportJobStore::new()))
+            .with_storage(storage.clone())
+            .with_inline_limit(3)
+            .with_page_size(2);
+        let columns = select_columns(RESOURCE_COLUMNS, Some("id,name")).unwrap();
+
+        let response = service.export(owner_id, source.clone(), ExportFormat::Csv, columns.clone()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        let body: Value = serde_json::from_slice(&hyper::body::to_bytes(response.into_body()).await.unwrap()).unwrap();
+        assert_eq!(body["status"], "pending");
+        let job_id: Uuid = body["id"].as_str().unwrap().parse().unwrap();
+
+        let mut status = service.job_status(owner_id, job_id).await.unwrap();
+        for _ in 0..100 {
+            if status.job.status == ExportStatus::Completed {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            status = service.job_status(owner_id, job_id).await.unwrap();
+        }
+        assert_eq!(status.job.status, ExportStatus::Completed);
+        assert_eq!(status.job.row_count, Some(5));
+        let key = status.job.object_key.clone().unwrap();
+        assert_eq!(status.download_url.unwrap(), format!("memory://{}?expires_in=900", key));
+        let contents = String::from_utf8(storage.object(&key).await.unwrap()).unwrap();
+        assert_eq!(contents.lines().count(), 6);
+        assert!(contents.starts_with("id,name\r\n"));
+
+        // Another tenant can't see the job
+        assert!(service.job_status(Uuid::new_v4(), job_id).await.is_err());
+
+        // Under the limit it's streamed straight back
+        let small = service.with_inline_limit(10);
+        let response = small.export(owner_id, source, ExportFormat::JsonLines, columns).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/x-ndjson");
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(String::from_utf8(body.to_vec()).unwrap().lines().count(), 5);
+    }
+}
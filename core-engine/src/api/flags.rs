@@ -0,0 +1,160 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, HeaderName, StatusCode},
+    Extension, Json,
+};
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{
+    db::DbPool,
+    error::AppResult,
+    flags::{FeatureFlag, FlagDefinition, FlagService},
+    middleware::{AuthUser, Scope},
+    models::version::{etag, expected_version},
+};
+
+use super::audit::record_audit;
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateFlagRequest {
+    #[serde(flatten)]
+    pub definition: FlagDefinition,
+    // Alternative to If-Match for clients that can't set headers
+    pub version: Option<i64>,
+}
+
+#[axum::debug_handler(state = DbPool)]
+pub async fn list_flags_handler(
+    Extension(flags): Extension<Arc<FlagService>>,
+    auth: AuthUser,
+) -> AppResult<Json<Vec<FeatureFlag>>> {
+    auth.require(Scope::ManageFlags)?;
+    Ok(Json(flags.list().await?))
+}
+
+#[axum::debug_handler]
+pub async fn create_flag_handler(
+    State(db): State<DbPool>,
+    Extension(flags): Extension<Arc<FlagService>>,
+    auth: AuthUser,
+    Json(definition): Json<FlagDefinition>,
+) -> AppResult<(StatusCode, [(HeaderName, String); 1], Json<FeatureFlag>)> {
+    auth.require(Scope::ManageFlags)?;
+    let flag = flags.create(definition, auth.user_id, Utc::now()).await?;
+    record_audit(&db, &auth, "flag.create", Some(flag.id), json!({ "key": flag.definition.key, "flag": flag.definition })).await;
+    Ok((StatusCode::CREATED, [(header::ETAG, etag(flag.version))], Json(flag)))
+}
+
+#[axum::debug_handler(state = DbPool)]
+pub async fn get_flag_handler(
+    Extension(flags): Extension<Arc<FlagService>>,
+    auth: AuthUser,
+    Path(key): Path<String>,
+) -> AppResult<([(HeaderName, String); 1], Json<FeatureFlag>)> {
+    auth.require(Scope::ManageFlags)?;
+    let flag = flags.get(&key).await?;
+    Ok(([(header::ETAG, etag(flag.version))], Json(flag)))
+}
+
+// Replaces the whole definition; a stale version comes back as a 409 with the current flag
+#[axum::debug_handler]
+pub async fn update_flag_handler(
+    State(db): State<DbPool>,
+    Extension(flags): Extension<Arc<FlagService>>,
+    auth: AuthUser,
+    Path(key): Path<String>,
+    headers: HeaderMap,
+    Json(request): Json<UpdateFlagRequest>,
+) -> AppResult<([(HeaderName, String); 1], Json<FeatureFlag>)> {
+    auth.require(Scope::ManageFlags)?;
+    let if_match = headers.get(header::IF_MATCH).and_then(|value| value.to_str().ok());
+    let expected = expected_version(if_match, request.version)?;
+    let previous = flags.get(&key).await?;
+    let flag = flags.update(&key, request.definition, expected, auth.user_id, Utc::now()).await?;
+    record_audit(
+        &db,
+        &auth,
+        "flag.update",
+        Some(flag.id),
+        json!({ "key": key, "version": flag.version, "previous": previous.definition, "flag": flag.definition }),
+    )
+    .await;
+    Ok(([(header::ETAG, etag(flag.version))], Json(flag)))
+}
+
+#[axum::debug_handler]
+pub async fn delete_flag_handler(
+    State(db): State<DbPool>,
+    Extension(flags): Extension<Arc<FlagService>>,
+    auth: AuthUser,
+    Path(key): Path<String>,
+) -> AppResult<StatusCode> {
+    auth.require(Scope::ManageFlags)?;
+    let previous = flags.get(&key).await?;
+    flags.delete(&key).await?;
+    record_audit(&db, &auth, "flag.delete", Some(previous.id), json!({ "key": key, "previous": previous.definition })).await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flags::InMemoryFlagStore;
+    use crate::middleware::{ApiKeyService, InMemoryApiKeyStore};
+    use axum::{body::Body, http::Request, routing::{get, put}, Router};
+    use sqlx::postgres::PgPoolOptions;
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn test_flag_admin_requires_manage_scope_and_versions() {
+        let flags = Arc::new(FlagService::new(Arc::new(InMemoryFlagStore::new())));
+        let keys = Arc::new(ApiKeyService::new(Arc::new(InMemoryApiKeyStore::new())));
+        let owner = Uuid::new_v4();
+        let user_scopes = Scope::for_role("user");
+        assert!(!user_scopes.contains(&Scope::ManageFlags));
+        let (_, user_key) = keys.issue(owner, owner, "ci", &user_scopes, None, Utc::now()).await.unwrap();
+        let (_, admin_key) = keys.issue(owner, owner, "flags", &[Scope::ManageFlags], None, Utc::now()).await.unwrap();
+        // Audit writes fail against the unreachable pool and are only logged
+        let pool = PgPoolOptions::new()
+            .acquire_timeout(std::time::Duration::from_millis(100))
+            .connect_lazy("postgresql://root@localhost:26257/sirsi_test")
+            .unwrap();
+        let app = Router::new()
+            .route("/admin/flags", get(list_flags_handler).post(create_flag_handler))
+            .route("/admin/flags/:key", put(update_flag_handler))
+            .layer(Extension(flags.clone()))
+            .layer(Extension(keys))
+            .with_state(pool);
+
+        let request = |method: &str, uri: &str, key: &str, body: &str| {
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("Authorization", format!("ApiKey {}", key))
+                .header("Content-Type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+        let body = r#"{"key":"dark-mode","kind":"boolean","enabled":true,"fallthrough":{"type":"off"}}"#;
+        let response = app.clone().oneshot(request("POST", "/admin/flags", &user_key, body)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = app.clone().oneshot(request("POST", "/admin/flags", &admin_key, body)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(response.headers()[header::ETAG], "\"1\"");
+
+        let update = r#"{"key":"dark-mode","kind":"boolean","enabled":true,"fallthrough":{"type":"on"}}"#;
+        let response = app.clone().oneshot(request("PUT", "/admin/flags/dark-mode", &admin_key, update)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PRECONDITION_REQUIRED);
+        let versioned = r#"{"key":"dark-mode","kind":"boolean","enabled":true,"fallthrough":{"type":"on"},"version":1}"#;
+        let response = app.clone().oneshot(request("PUT", "/admin/flags/dark-mode", &admin_key, versioned)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = app.clone().oneshot(request("PUT", "/admin/flags/dark-mode", &admin_key, versioned)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        assert_eq!(flags.get("dark-mode").await.unwrap().version, 2);
+    }
+}
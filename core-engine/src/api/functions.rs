@@ -0,0 +1,139 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{BodyStream, Path},
+    http::{header, HeaderMap, StatusCode},
+    Extension, Json,
+};
+use futures::{StreamExt, TryStreamExt};
+use sirsi_compute_manager::serverless::{CodeRef, CodeStore};
+use sirsi_compute_manager::error::ComputeError;
+
+use crate::{
+    db::DbPool,
+    error::{AppError, AppResult},
+    metering::{MeteringService, Quota},
+    middleware::{AuthUser, Scope},
+};
+
+use super::BodyLimits;
+
+pub const FUNCTION_CODE_ROUTE: &str = "/functions/:name/code";
+
+fn compute_error(error: ComputeError) -> AppError {
+    match error {
+        ComputeError::TooLarge(msg) => AppError::PayloadTooLarge(msg),
+        ComputeError::Validation(msg) => AppError::Validation(msg),
+        other => AppError::Internal(other.to_string()),
+    }
+}
+
+fn validate_function_name(name: &str) -> AppResult<()> {
+    let valid = !name.is_empty()
+        && name.len() <= 64
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(AppError::Validation(format!("Invalid function name '{}'", name)))
+    }
+}
+
+// Streams the request body into the code store chunk by chunk, so the package never has to
+// fit in memory or go through JSON. Deploy with the returned reference as `code_ref`.
+#[axum::debug_handler(state = DbPool)]
+pub async fn upload_function_code_handler(
+    Extension(store): Extension<Arc<dyn CodeStore>>,
+    Extension(limits): Extension<BodyLimits>,
+    Extension(metering): Extension<Arc<MeteringService>>,
+    auth: AuthUser,
+    Path(name): Path<String>,
+    headers: HeaderMap,
+    body: BodyStream,
+) -> AppResult<(StatusCode, Json<CodeRef>)> {
+    auth.require(Scope::WriteFunctions)?;
+    validate_function_name(&name)?;
+
+    let max_bytes = limits.for_route(FUNCTION_CODE_ROUTE) as u64;
+    let declared = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+    if declared.is_some_and(|length| length > max_bytes) {
+        return Err(AppError::PayloadTooLarge(format!("Function code exceeds {} bytes", max_bytes)));
+    }
+
+    // Counted once per function name; new code for an existing function is free
+    let counted = metering.acquire(auth.user_id, Quota::MaxFunctions, Some(&name)).await?;
+
+    let chunks = body
+        .map_err(|e| ComputeError::Validation(format!("Failed to read upload: {}", e)))
+        .boxed();
+    let code_ref = match store.put(&format!("{}/{}", auth.user_id, name), chunks, max_bytes).await {
+        Ok(code_ref) => code_ref,
+        Err(e) => {
+            if counted {
+                metering.release(auth.user_id, Quota::MaxFunctions, Some(&name)).await?;
+            }
+            return Err(compute_error(e));
+        }
+    };
+
+    Ok((StatusCode::CREATED, Json(code_ref)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metering::InMemoryUsageStore;
+    use crate::middleware::{ApiKeyService, InMemoryApiKeyStore};
+    use axum::{body::Body, http::Request, routing::post, Router};
+    use chrono::Utc;
+    use sirsi_compute_manager::serverless::{code::sha256_digest, FileCodeStore};
+    use sqlx::postgres::PgPoolOptions;
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn test_streamed_upload_returns_verified_code_ref() {
+        let dir = tempfile::tempdir().unwrap();
+        let store: Arc<dyn CodeStore> = Arc::new(FileCodeStore::new(dir.path()));
+        let keys = Arc::new(ApiKeyService::new(Arc::new(InMemoryApiKeyStore::new())));
+        let owner = Uuid::new_v4();
+        let (_, key) = keys.issue(owner, owner, "ci", &[Scope::WriteFunctions], None, Utc::now()).await.unwrap();
+        let limits = BodyLimits::new(1024).with_route(FUNCTION_CODE_ROUTE, 8 * 1024 * 1024);
+        let pool = PgPoolOptions::new().connect_lazy("postgresql://root@localhost:26257/sirsi_test").unwrap();
+        let app = Router::new()
+            .route(FUNCTION_CODE_ROUTE, post(upload_function_code_handler))
+            .layer(Extension(store))
+            .layer(Extension(limits))
+            .layer(Extension(Arc::new(MeteringService::new(Arc::new(InMemoryUsageStore::new())))))
+            .layer(Extension(keys))
+            .with_state(pool);
+
+        // 5MB sent as a chunked body with no Content-Length, well past the 1KB default limit
+        let package: Vec<u8> = (0..5 * 1024 * 1024u32).map(|i| (i % 251) as u8).collect();
+        let upload = |bytes: Vec<u8>| {
+            let chunks: Vec<Result<Vec<u8>, std::io::Error>> = bytes.chunks(256 * 1024).map(|c| Ok(c.to_vec())).collect();
+            Request::builder()
+                .method("POST")
+                .uri("/functions/resize-images/code")
+                .header("Authorization", format!("ApiKey {}", key))
+                .body(Body::wrap_stream(futures::stream::iter(chunks)))
+                .unwrap()
+        };
+        let response = app.clone().oneshot(upload(package.clone())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let code_ref: CodeRef = serde_json::from_slice(&hyper::body::to_bytes(response.into_body()).await.unwrap()).unwrap();
+        assert_eq!(code_ref.digest, sha256_digest(&package));
+        assert_eq!(code_ref.size_bytes, package.len() as u64);
+        assert_eq!(code_ref.load().await.unwrap(), package);
+
+        let response = app.clone().oneshot(upload(vec![0u8; 9 * 1024 * 1024])).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let mut traversal = upload(vec![1, 2, 3]);
+        *traversal.uri_mut() = "/functions/..%2F..%2Fetc/code".parse().unwrap();
+        assert_eq!(app.oneshot(traversal).await.unwrap().status(), StatusCode::BAD_REQUEST);
+    }
+}
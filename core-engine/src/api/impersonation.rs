@@ -0,0 +1,179 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::{
+    db::DbPool,
+    error::{AppError, AppResult},
+    middleware::{AuthUser, ImpersonationService, Scope},
+    models::{user::User, AuditEvent, ImpersonationSession},
+};
+
+use super::audit::record_audit;
+
+#[derive(Debug, Deserialize)]
+pub struct StartImpersonationRequest {
+    pub target_id: Uuid,
+    pub reason: String,
+    pub duration_minutes: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImpersonationResponse {
+    pub session: ImpersonationSession,
+    // Bearer token for the target; only ever returned here
+    pub token: String,
+}
+
+// The impersonated tenant's own log gets an entry for every session change, whoever made it
+async fn record_for_target(db: &DbPool, session: &ImpersonationSession, actor_id: Uuid, action: &str) {
+    let details = json!({
+        "impersonator_id": session.actor_id,
+        "reason": session.reason,
+        "expires_at": session.expires_at,
+        "revoked_by": session.revoked_by,
+    });
+    if let Err(e) =
+        AuditEvent::record(db, session.target_id, actor_id, action, "impersonation", Some(session.id), details).await
+    {
+        warn!("Failed to record audit event {} for {}: {}", action, session.id, e);
+    }
+}
+
+#[axum::debug_handler]
+pub async fn start_impersonation_handler(
+    State(db): State<DbPool>,
+    Extension(impersonation): Extension<Arc<ImpersonationService>>,
+    auth: AuthUser,
+    Json(payload): Json<StartImpersonationRequest>,
+) -> AppResult<(StatusCode, Json<ImpersonationResponse>)> {
+    auth.require(Scope::Impersonate)?;
+    auth.require_direct("Starting another impersonation")?;
+    User::find_by_id(&db, payload.target_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".into()))?;
+
+    let duration = payload.duration_minutes.map(Duration::minutes);
+    let (session, token) =
+        impersonation.start(auth.user_id, payload.target_id, &payload.reason, duration, Utc::now()).await?;
+    record_audit(
+        &db,
+        &auth,
+        "impersonation.start",
+        Some(session.id),
+        json!({ "target_id": session.target_id, "reason": session.reason, "expires_at": session.expires_at }),
+    )
+    .await;
+    record_for_target(&db, &session, auth.user_id, "impersonation.start").await;
+
+    Ok((StatusCode::CREATED, Json(ImpersonationResponse { session, token })))
+}
+
+#[axum::debug_handler(state = DbPool)]
+pub async fn list_started_impersonations_handler(
+    Extension(impersonation): Extension<Arc<ImpersonationService>>,
+    auth: AuthUser,
+) -> AppResult<Json<Vec<ImpersonationSession>>> {
+    auth.require(Scope::Impersonate)?;
+    Ok(Json(impersonation.list_for_actor(auth.user_id).await?))
+}
+
+// Sessions in which the caller's tenant was impersonated
+#[axum::debug_handler(state = DbPool)]
+pub async fn list_impersonation_sessions_handler(
+    Extension(impersonation): Extension<Arc<ImpersonationService>>,
+    auth: AuthUser,
+) -> AppResult<Json<Vec<ImpersonationSession>>> {
+    auth.require(Scope::ReadAudit)?;
+    Ok(Json(impersonation.list_for_target(auth.user_id).await?))
+}
+
+#[axum::debug_handler]
+pub async fn revoke_impersonation_handler(
+    State(db): State<DbPool>,
+    Extension(impersonation): Extension<Arc<ImpersonationService>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<ImpersonationSession>> {
+    let session = impersonation.revoke(id, auth.actor_id(), Utc::now()).await?;
+    if auth.user_id != session.target_id {
+        record_audit(&db, &auth, "impersonation.revoke", Some(id), json!({ "target_id": session.target_id })).await;
+    }
+    record_for_target(&db, &session, auth.actor_id(), "impersonation.revoke").await;
+    Ok(Json(session))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{api_keys::rotate_api_key_handler, audit::audit_identity, projects::delete_project_handler};
+    use crate::metering::{InMemoryUsageStore, MeteringService};
+    use crate::middleware::{ApiKeyService, Impersonator, InMemoryApiKeyStore};
+    use crate::orgs::{InMemoryOrgStore, OrgService};
+    use sqlx::postgres::PgPoolOptions;
+
+    fn impersonated(customer: Uuid, admin: Uuid) -> AuthUser {
+        let user = User {
+            id: customer,
+            name: "customer".into(),
+            email: "customer@example.com".into(),
+            password_hash: String::new(),
+            created_at: None,
+            updated_at: None,
+        };
+        AuthUser {
+            user,
+            user_id: customer,
+            scopes: Scope::for_role("user"),
+            api_key_id: None,
+            impersonator: Some(Impersonator { actor_id: admin, session_id: Uuid::new_v4() }),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_impersonated_calls_record_both_identities_and_block_credential_changes() {
+        let (customer, admin) = (Uuid::new_v4(), Uuid::new_v4());
+        let auth = impersonated(customer, admin);
+        let mut details = json!({ "name": "web" });
+        let (owner_id, actor_id) = audit_identity(&auth, &mut details);
+        assert_eq!((owner_id, actor_id), (customer, admin));
+        assert_eq!(details["impersonated_user_id"], customer.to_string());
+        assert_eq!(details["impersonator_id"], admin.to_string());
+        assert_eq!(details["impersonation_session_id"], auth.impersonator.unwrap().session_id.to_string());
+
+        // Blocked before any lookup, so the unreachable pool is never used
+        let pool = PgPoolOptions::new()
+            .acquire_timeout(std::time::Duration::from_millis(100))
+            .connect_lazy("postgresql://root@localhost:26257/sirsi_test")
+            .unwrap();
+        let metering = Arc::new(MeteringService::new(Arc::new(InMemoryUsageStore::new())));
+        let orgs = Arc::new(OrgService::new(Arc::new(InMemoryOrgStore::new())));
+        let deleted = delete_project_handler(
+            State(pool.clone()),
+            Extension(metering),
+            Extension(orgs),
+            impersonated(customer, admin),
+            Path(Uuid::new_v4()),
+        )
+        .await;
+        assert!(matches!(deleted, Err(AppError::Forbidden(_))));
+        let keys = Arc::new(ApiKeyService::new(Arc::new(InMemoryApiKeyStore::new())));
+        let rotated =
+            rotate_api_key_handler(State(pool), Extension(keys), impersonated(customer, admin), Path(Uuid::new_v4())).await;
+        assert!(matches!(rotated, Err(AppError::Forbidden(_))));
+
+        // The same caller without an impersonator gets past the guard
+        let direct = AuthUser { impersonator: None, ..impersonated(customer, admin) };
+        assert!(direct.require_direct("Deleting a project").is_ok());
+        assert_eq!(direct.actor_id(), customer);
+    }
+}
@@ -0,0 +1,98 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    db::DbPool,
+    error::AppResult,
+    inventory::{InventoryPage, InventoryQuery, InventoryService, NewSavedSearch, SavedSearch, DEFAULT_PAGE_SIZE},
+    middleware::{AuthUser, Scope},
+};
+
+use super::audit::record_audit;
+
+#[derive(Debug, Deserialize)]
+pub struct SearchRequest {
+    #[serde(flatten)]
+    pub query: InventoryQuery,
+    // `next_cursor` from the previous page
+    pub after: Option<Uuid>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PageParams {
+    pub after: Option<Uuid>,
+    pub limit: Option<i64>,
+}
+
+#[axum::debug_handler(state = DbPool)]
+pub async fn search_inventory_handler(
+    Extension(inventory): Extension<Arc<InventoryService>>,
+    auth: AuthUser,
+    Json(request): Json<SearchRequest>,
+) -> AppResult<Json<InventoryPage>> {
+    auth.require(Scope::ReadResources)?;
+    let limit = request.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    Ok(Json(inventory.search(auth.user_id, &request.query, request.after, limit).await?))
+}
+
+#[axum::debug_handler]
+pub async fn create_saved_search_handler(
+    State(db): State<DbPool>,
+    Extension(inventory): Extension<Arc<InventoryService>>,
+    auth: AuthUser,
+    Json(search): Json<NewSavedSearch>,
+) -> AppResult<(StatusCode, Json<SavedSearch>)> {
+    auth.require(Scope::ReadResources)?;
+    let search = inventory.create_saved_search(auth.user_id, search, Utc::now()).await?;
+    record_audit(&db, &auth, "inventory.saved_search_create", Some(search.id), json!({
+        "name": search.name,
+        "recipients": search.recipients.len(),
+    })).await;
+    Ok((StatusCode::CREATED, Json(search)))
+}
+
+#[axum::debug_handler(state = DbPool)]
+pub async fn list_saved_searches_handler(
+    Extension(inventory): Extension<Arc<InventoryService>>,
+    auth: AuthUser,
+) -> AppResult<Json<Vec<SavedSearch>>> {
+    auth.require(Scope::ReadResources)?;
+    Ok(Json(inventory.saved_searches(auth.user_id).await?))
+}
+
+#[axum::debug_handler]
+pub async fn delete_saved_search_handler(
+    State(db): State<DbPool>,
+    Extension(inventory): Extension<Arc<InventoryService>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> AppResult<StatusCode> {
+    auth.require(Scope::ReadResources)?;
+    inventory.delete_saved_search(auth.user_id, id).await?;
+    record_audit(&db, &auth, "inventory.saved_search_delete", Some(id), json!({})).await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Runs a saved search against the current inventory
+#[axum::debug_handler(state = DbPool)]
+pub async fn saved_search_results_handler(
+    Extension(inventory): Extension<Arc<InventoryService>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+    Query(params): Query<PageParams>,
+) -> AppResult<Json<InventoryPage>> {
+    auth.require(Scope::ReadResources)?;
+    let search = inventory.saved_search(auth.user_id, id).await?;
+    let limit = params.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    Ok(Json(inventory.search(auth.user_id, &search.query, params.after, limit).await?))
+}
@@ -1,36 +1,444 @@
 use axum::{
+    extract::DefaultBodyLimit,
     routing::{get, post, put, delete},
-    Router,
+    Extension, Router,
 };
+use sirsi_compute_manager::fleet::CommandRunner;
+use sirsi_compute_manager::serverless::{CodeStore, FileCodeStore};
 use sqlx::PgPool; // CockroachDB uses PostgreSQL protocol
+use std::collections::HashMap;
+use std::sync::Arc;
 
+use crate::health::{self, HealthRegistry, PgPoolCheck};
+
+mod api_keys;
+mod audit;
 mod auth;
+mod bastion;
+mod budgets;
+mod commands;
+mod compliance;
+mod discovery;
+mod entitlements;
+pub mod export;
+mod flags;
+pub mod functions;
+mod impersonation;
+mod inventory;
+mod orgs;
+mod privacy;
 mod projects;
+mod provider_events;
+mod remediation;
+mod reports;
 mod resources;
+mod retention;
+mod usage;
+mod warehouse;
+mod webhooks;
+mod workflow_runs;
+
+use crate::bastion::SessionBroker;
+use crate::budgets::{BudgetService, CreationBlockHook, PgBudgetStore};
+use crate::compliance::{ComplianceService, PgComplianceStore};
+use crate::device::{DeviceAuthService, PgDeviceStore};
+use crate::discovery::{DiscoveryService, PgDiscoveryStore};
+use crate::entitlements::{EntitlementService, PgEntitlementStore};
+use crate::flags::{FlagService, PgFlagStore};
+use crate::inventory::{InventoryService, PgInventoryStore};
+use crate::metering::{MeteringService, PgUsageStore};
+use crate::orgs::{OrgService, PgOrgStore};
+use crate::password::{LocalAuthService, LoginThrottle, PgCredentialStore, PgLockoutStore};
+use crate::middleware::{ApiKeyService, ImpersonationService, PgApiKeyStore, PgImpersonationStore};
+use crate::models::PgAuditRetention;
+use crate::provider_events::{PgProviderEventStore, ProviderEventService};
+use crate::privacy::{pg_subject_tables, PgPrivacyAudit, PgPrivacyStore, PgSubjectDirectory, PrivacyService};
+use crate::remediation::{PgRemediationStore, RemediationService};
+use crate::reporting::{PgReportStore, ReportService};
+use crate::retention::{PgRetentionStore, RetentionService};
+use crate::warehouse::WarehouseService;
+use crate::webhooks::{PgWebhookStore, WebhookService};
+use crate::workflow_runs::{PgWorkflowRunStore, WorkflowRunService};
+use export::{ExportService, PgExportJobStore};
+use functions::FUNCTION_CODE_ROUTE;
+
+const DEFAULT_BODY_LIMIT: usize = 2 * 1024 * 1024;
+const FUNCTION_CODE_LIMIT: usize = 250 * 1024 * 1024;
+const DISCOVERY_RUN_LIMIT: usize = 64 * 1024 * 1024;
+
+// Maximum request body per route pattern, falling back to a global default
+#[derive(Debug, Clone)]
+pub struct BodyLimits {
+    default: usize,
+    routes: HashMap<&'static str, usize>,
+}
+
+impl Default for BodyLimits {
+    fn default() -> Self {
+        Self::new(DEFAULT_BODY_LIMIT)
+            .with_route(FUNCTION_CODE_ROUTE, FUNCTION_CODE_LIMIT)
+            .with_route("/discovery/runs", DISCOVERY_RUN_LIMIT)
+    }
+}
+
+impl BodyLimits {
+    pub fn new(default: usize) -> Self {
+        Self { default, routes: HashMap::new() }
+    }
+
+    pub fn with_route(mut self, route: &'static str, max_bytes: usize) -> Self {
+        self.routes.insert(route, max_bytes);
+        self
+    }
+
+    pub fn default_limit(&self) -> usize {
+        self.default
+    }
+
+    pub fn for_route(&self, route: &str) -> usize {
+        self.routes.get(route).copied().unwrap_or(self.default)
+    }
+
+    // Applies to the buffering extractors (Json, Bytes); streamed uploads check `for_route`
+    fn layer(&self, route: &str) -> DefaultBodyLimit {
+        DefaultBodyLimit::max(self.for_route(route))
+    }
+}
+
+pub struct ApiServices {
+    // `/health`, `/health/live` and `/health/ready` report on the registry's checks
+    pub health: Arc<HealthRegistry>,
+    pub exports: Arc<ExportService>,
+    pub api_keys: Arc<ApiKeyService>,
+    pub function_code: Arc<dyn CodeStore>,
+    pub metering: Arc<MeteringService>,
+    // Plan tiers and per-tenant overrides; limits are counted with the metering quota counters
+    pub entitlements: Arc<EntitlementService>,
+    pub discovery: Arc<DiscoveryService>,
+    // Also updated by each discovery run; saved searches only notify once a notifier is attached
+    pub inventory: Arc<InventoryService>,
+    // Scans the inventory after each discovery run and on demand
+    pub compliance: Arc<ComplianceService>,
+    // Runs fail with a configuration error until a workflow executor is attached, and stay
+    // unverified without a rescanner. Auto-remediation runs wherever `spawn_worker` is started
+    pub remediation: Arc<RemediationService>,
+    // AWS pushes resource state changes here between discovery runs; applied changes are queued
+    // as webhook events and broadcast to `subscribe()` receivers
+    pub provider_events: Arc<ProviderEventService>,
+    // Report runs fail with a configuration error until a data source is attached
+    pub reports: Arc<ReportService>,
+    pub body_limits: BodyLimits,
+    // Fleet command routes answer with a configuration error until a runner is attached
+    pub commands: Option<Arc<CommandRunner>>,
+    // SSH session routes answer with a configuration error until a broker is attached
+    pub bastion: Option<Arc<SessionBroker>>,
+    // Warehouse export routes answer with a configuration error until a service with a secret
+    // manager for destination credentials is attached; scheduled runs need `spawn_scheduler`
+    pub warehouse: Option<Arc<WarehouseService>>,
+    // Admin flag changes; in-process evaluation goes through a FlagClient on the same service
+    pub flags: Arc<FlagService>,
+    pub impersonation: Arc<ImpersonationService>,
+    pub orgs: Arc<OrgService>,
+    pub devices: Arc<DeviceAuthService>,
+    // Unlock links aren't sent until an unlock notifier is attached
+    pub passwords: Arc<LocalAuthService>,
+    // Outbox delivery runs wherever `spawn_dispatcher` is started; these routes only manage endpoints
+    pub webhooks: Arc<WebhookService>,
+    // Purges run wherever `spawn_purger` is started; these routes manage policies and show the log
+    pub retention: Arc<RetentionService>,
+    // Exports wait for storage to be attached; agent sessions live in Redis and are added with
+    // `with_table(context_store)`. Approved requests run wherever `spawn_worker` is started
+    pub privacy: Arc<PrivacyService>,
+    // Evaluation fails with a configuration error until a cost source is attached; fleet stops need
+    // a `FleetStopHook`. Budgets are evaluated wherever `spawn_evaluator` is started
+    pub budgets: Arc<BudgetService>,
+    // Run history reported by the workflow executors, searchable across a project's workflows
+    pub workflow_runs: Arc<WorkflowRunService>,
+}
+
+impl ApiServices {
+    // Background exports stay disabled until the export service is given storage
+    pub fn new(db: &PgPool) -> Self {
+        let usage = Arc::new(PgUsageStore::new(db.clone()));
+        let metering = Arc::new(MeteringService::new(usage.clone()));
+        let inventory = Arc::new(InventoryService::new(Arc::new(PgInventoryStore::new(db.clone()))));
+        let webhooks = Arc::new(WebhookService::new(Arc::new(PgWebhookStore::new(db.clone()))));
+        let compliance = Arc::new(
+            ComplianceService::new(Arc::new(PgComplianceStore::new(db.clone()))).with_resources(inventory.clone()),
+        );
+        Self {
+            health: Arc::new(HealthRegistry::new().with_critical("database", Arc::new(PgPoolCheck::new(db.clone())))),
+            exports: Arc::new(ExportService::new(Arc::new(PgExportJobStore::new(db.clone())))),
+            api_keys: Arc::new(ApiKeyService::new(Arc::new(PgApiKeyStore::new(db.clone())))),
+            function_code: Arc::new(FileCodeStore::new(std::env::temp_dir().join("sirsi-function-code"))),
+            metering: metering.clone(),
+            entitlements: Arc::new(EntitlementService::new(
+                Arc::new(PgEntitlementStore::new(db.clone())),
+                metering.clone(),
+            )),
+            budgets: Arc::new(
+                BudgetService::new(Arc::new(PgBudgetStore::new(db.clone())))
+                    .with_hook(Arc::new(CreationBlockHook::new(metering.clone()))),
+            ),
+            discovery: Arc::new(
+                DiscoveryService::new(Arc::new(PgDiscoveryStore::new(db.clone())))
+                    .with_inventory(inventory.clone())
+                    .with_compliance(compliance.clone()),
+            ),
+            remediation: Arc::new(
+                RemediationService::new(Arc::new(PgRemediationStore::new(db.clone())), compliance.clone())
+                    .with_inventory(inventory.clone()),
+            ),
+            provider_events: Arc::new(
+                ProviderEventService::new(Arc::new(PgProviderEventStore::new(db.clone())), inventory.clone())
+                    .with_webhooks(webhooks.clone()),
+            ),
+            inventory,
+            compliance,
+            reports: Arc::new(ReportService::new(Arc::new(PgReportStore::new(db.clone()))).with_metering(metering)),
+            body_limits: BodyLimits::default(),
+            commands: None,
+            bastion: None,
+            warehouse: None,
+            flags: Arc::new(FlagService::new(Arc::new(PgFlagStore::new(db.clone())))),
+            impersonation: Arc::new(ImpersonationService::new(Arc::new(PgImpersonationStore::new(db.clone())))),
+            orgs: Arc::new(OrgService::new(Arc::new(PgOrgStore::new(db.clone())))),
+            devices: Arc::new(DeviceAuthService::new(Arc::new(PgDeviceStore::new(db.clone())))),
+            passwords: Arc::new(LocalAuthService::new(
+                Arc::new(PgCredentialStore::new(db.clone())),
+                LoginThrottle::new(Arc::new(PgLockoutStore::new(db.clone()))),
+            )),
+            webhooks,
+            retention: Arc::new(
+                RetentionService::new(Arc::new(PgRetentionStore::new(db.clone())))
+                    .with_target(Arc::new(PgAuditRetention::new(db.clone())))
+                    .with_target(usage),
+            ),
+            privacy: Arc::new(
+                PrivacyService::new(Arc::new(PgPrivacyStore::new(db.clone())), Arc::new(PgSubjectDirectory::new(db.clone())))
+                    .with_tables(pg_subject_tables(db))
+                    .with_audit(Arc::new(PgPrivacyAudit::new(db.clone()))),
+            ),
+            workflow_runs: Arc::new(WorkflowRunService::new(Arc::new(PgWorkflowRunStore::new(db.clone())))),
+        }
+    }
+}
 
 pub fn create_router(db: PgPool) -> Router {
-    Router::new()
-        .route("/health", get(health_check))
+    let services = ApiServices::new(&db);
+    create_router_with(db, services)
+}
+
+pub fn create_router_with(db: PgPool, services: ApiServices) -> Router {
+    let limits = services.body_limits.clone();
+    let router = Router::new()
+        .merge(health::router(services.health))
         // Auth routes
-        .route("/auth/register", post(auth::register_handler))
-        .route("/auth/login", post(auth::login_handler))
+        .route("/auth/register", post(auth::register_handler).layer(limits.layer("/auth/register")))
+        .route("/auth/login", post(auth::login_handler).layer(limits.layer("/auth/login")))
+        .route("/auth/password", post(auth::change_password_handler).layer(limits.layer("/auth/password")))
+        .route("/auth/unlock", post(auth::redeem_unlock_handler).layer(limits.layer("/auth/unlock")))
+        .route("/auth/unlock/request", post(auth::request_unlock_handler).layer(limits.layer("/auth/unlock/request")))
+        .route("/auth/device", post(auth::device_code_handler).layer(limits.layer("/auth/device")))
+        .route("/auth/device/verify", post(auth::verify_device_handler).layer(limits.layer("/auth/device/verify")))
+        .route("/auth/token", post(auth::token_handler).layer(limits.layer("/auth/token")))
         // Projects routes
         .route("/projects", get(projects::list_projects_handler))
-        .route("/projects", post(projects::create_project_handler))
+        .route("/projects", post(projects::create_project_handler).layer(limits.layer("/projects")))
         .route("/projects/:id", get(projects::get_project_handler))
-        .route("/projects/:id", put(projects::update_project_handler))
+        .route("/projects/:id", put(projects::update_project_handler).layer(limits.layer("/projects/:id")))
         .route("/projects/:id", delete(projects::delete_project_handler))
+        .route("/projects/:id/transfer", post(projects::transfer_project_handler).layer(limits.layer("/projects/:id/transfer")))
+        // Workflow run history
+        .route("/projects/:id/workflow-runs", post(workflow_runs::record_workflow_run_handler).layer(limits.layer("/projects/:id/workflow-runs")))
+        .route("/projects/:id/workflow-runs/search", post(workflow_runs::search_workflow_runs_handler).layer(limits.layer("/projects/:id/workflow-runs/search")))
+        .route("/projects/:id/workflow-runs/failures", get(workflow_runs::workflow_failures_handler))
+        .route("/projects/:id/workflow-runs/:run_id", get(workflow_runs::get_workflow_run_handler))
+        // Organization routes
+        .route("/orgs", get(orgs::list_orgs_handler))
+        .route("/orgs", post(orgs::create_org_handler).layer(limits.layer("/orgs")))
+        .route("/orgs/:id", get(orgs::get_org_handler))
+        .route("/orgs/:id", put(orgs::update_org_handler).layer(limits.layer("/orgs/:id")))
+        .route("/orgs/:id", delete(orgs::delete_org_handler))
+        .route("/orgs/:id/members", get(orgs::list_members_handler))
+        .route("/orgs/:id/members/:user_id", put(orgs::set_member_role_handler).layer(limits.layer("/orgs/:id/members/:user_id")))
+        .route("/orgs/:id/members/:user_id", delete(orgs::remove_member_handler))
+        .route("/orgs/:id/invitations", post(orgs::create_invitation_handler).layer(limits.layer("/orgs/:id/invitations")))
+        .route("/orgs/:id/usage", get(orgs::org_usage_handler))
+        .route("/invitations/accept", post(orgs::accept_invitation_handler).layer(limits.layer("/invitations/accept")))
         // Resources routes
         .route("/resources", get(resources::list_resources_handler))
-        .route("/resources", post(resources::create_resource_handler))
+        .route("/resources", post(resources::create_resource_handler).layer(limits.layer("/resources")))
+        .route("/resources/export", get(export::export_resources_handler))
         .route("/resources/:id", get(resources::get_resource_handler))
-        .route("/resources/:id", put(resources::update_resource_handler))
+        .route("/resources/:id", put(resources::update_resource_handler).layer(limits.layer("/resources/:id")))
         .route("/resources/:id", delete(resources::delete_resource_handler))
-        .with_state(db)
-}
-
-pub async fn health_check() -> &'static str {
-    "OK"
+        .route("/resources/:id/impact", get(resources::resource_impact_handler))
+        .route("/resources/:id/links", post(resources::create_resource_link_handler).layer(limits.layer("/resources/:id/links")))
+        .route("/resources/:id/links/:link_id", delete(resources::delete_resource_link_handler))
+        // Discovery runs
+        .route("/discovery/runs", get(discovery::list_discovery_runs_handler))
+        .route("/discovery/runs", post(discovery::record_discovery_run_handler).layer(limits.layer("/discovery/runs")))
+        .route("/discovery/runs/:id", get(discovery::get_discovery_run_handler))
+        .route("/discovery/runs/:id/diff", get(discovery::diff_discovery_run_handler))
+        // Inventory search
+        .route("/inventory/search", post(inventory::search_inventory_handler).layer(limits.layer("/inventory/search")))
+        .route("/inventory/saved-searches", get(inventory::list_saved_searches_handler))
+        .route("/inventory/saved-searches", post(inventory::create_saved_search_handler).layer(limits.layer("/inventory/saved-searches")))
+        .route("/inventory/saved-searches/:id", delete(inventory::delete_saved_search_handler))
+        .route("/inventory/saved-searches/:id/results", get(inventory::saved_search_results_handler))
+        // Compliance
+        .route("/compliance/rules", get(compliance::list_compliance_rules_handler))
+        .route("/compliance/rules", post(compliance::create_compliance_rule_handler).layer(limits.layer("/compliance/rules")))
+        .route("/compliance/rules/:key", put(compliance::set_compliance_rule_enabled_handler).layer(limits.layer("/compliance/rules/:key")))
+        .route("/compliance/rules/:key", delete(compliance::delete_compliance_rule_handler))
+        .route("/compliance/findings", get(compliance::list_compliance_findings_handler))
+        .route("/compliance/scans", post(compliance::run_compliance_scan_handler))
+        .route("/compliance/summary", get(compliance::compliance_summary_handler))
+        .route("/events/aws/:source_id", post(provider_events::receive_aws_event_handler).layer(limits.layer("/events/aws/:source_id")))
+        .route("/events/sources", get(provider_events::list_event_sources_handler))
+        .route("/events/sources", post(provider_events::create_event_source_handler).layer(limits.layer("/events/sources")))
+        .route("/events/sources/:id", delete(provider_events::delete_event_source_handler))
+        .route("/events/unmapped", get(provider_events::list_unmapped_event_types_handler))
+        .route("/events/unmapped/events", get(provider_events::list_unmapped_events_handler))
+        .route("/compliance/findings/:id/remediate", post(remediation::remediate_finding_handler))
+        .route("/compliance/remediation-mappings", get(remediation::list_remediation_mappings_handler))
+        .route("/compliance/remediation-mappings", post(remediation::create_remediation_mapping_handler))
+        .route("/compliance/remediation-mappings/:id", put(remediation::update_remediation_mapping_handler))
+        .route("/compliance/remediation-mappings/:id", delete(remediation::delete_remediation_mapping_handler))
+        .route("/compliance/remediations", get(remediation::list_remediations_handler))
+        .route("/compliance/remediations/:id", get(remediation::get_remediation_handler))
+        // Webhooks
+        .route("/webhooks", get(webhooks::list_webhooks_handler))
+        .route("/webhooks", post(webhooks::create_webhook_handler).layer(limits.layer("/webhooks")))
+        .route("/webhooks/:id", get(webhooks::get_webhook_handler))
+        .route("/webhooks/:id", put(webhooks::update_webhook_handler).layer(limits.layer("/webhooks/:id")))
+        .route("/webhooks/:id", delete(webhooks::delete_webhook_handler))
+        .route("/webhooks/:id/test", post(webhooks::test_webhook_handler))
+        .route("/webhooks/:id/resume", post(webhooks::resume_webhook_handler))
+        .route("/webhooks/:id/deliveries", get(webhooks::list_webhook_deliveries_handler))
+        .route("/webhooks/:id/redeliver", post(webhooks::redeliver_webhook_handler).layer(limits.layer("/webhooks/:id/redeliver")))
+        // Scheduled reports
+        .route("/reports", get(reports::list_reports_handler))
+        .route("/reports", post(reports::create_report_handler).layer(limits.layer("/reports")))
+        .route("/reports/:id", get(reports::get_report_handler))
+        .route("/reports/:id", delete(reports::delete_report_handler))
+        .route("/reports/:id/run", post(reports::run_report_handler))
+        .route("/reports/:id/runs", get(reports::list_report_runs_handler))
+        .route("/report-runs/:id", get(reports::get_report_run_handler))
+        .route("/report-runs/:id/artifacts/:name", get(reports::download_report_artifact_handler))
+        // Warehouse exports
+        .route("/warehouse/datasets", get(warehouse::list_datasets_handler))
+        .route("/warehouse/destinations", get(warehouse::list_destinations_handler))
+        .route("/warehouse/destinations", post(warehouse::create_destination_handler).layer(limits.layer("/warehouse/destinations")))
+        .route("/warehouse/destinations/:id", delete(warehouse::delete_destination_handler))
+        .route("/warehouse/exports", get(warehouse::list_exports_handler))
+        .route("/warehouse/exports", post(warehouse::create_export_handler).layer(limits.layer("/warehouse/exports")))
+        .route("/warehouse/exports/:id", get(warehouse::get_export_handler))
+        .route("/warehouse/exports/:id", delete(warehouse::delete_export_handler))
+        .route("/warehouse/exports/:id/run", post(warehouse::run_export_handler))
+        .route("/warehouse/exports/:id/runs", get(warehouse::list_export_runs_handler))
+        .route("/warehouse/exports/:id/accept-schema", post(warehouse::accept_export_schema_handler).layer(limits.layer("/warehouse/exports/:id/accept-schema")))
+        // Audit routes
+        .route("/audit", get(audit::list_audit_handler))
+        .route("/audit/export", get(export::export_audit_handler))
+        .route("/exports/:id", get(export::get_export_job_handler))
+        // API key routes
+        .route("/api-keys", get(api_keys::list_api_keys_handler))
+        .route("/api-keys", post(api_keys::create_api_key_handler).layer(limits.layer("/api-keys")))
+        .route("/api-keys/:id/rotate", post(api_keys::rotate_api_key_handler))
+        .route("/api-keys/:id", delete(api_keys::revoke_api_key_handler))
+        // Usage and quotas
+        .route("/usage", get(usage::get_usage_handler))
+        .route("/admin/usage", get(usage::admin_usage_handler))
+        .route("/admin/tenants/:id/quotas", get(usage::get_tenant_quotas_handler))
+        .route("/admin/tenants/:id/quotas", put(usage::set_tenant_quotas_handler).layer(limits.layer("/admin/tenants/:id/quotas")))
+        // Plans and entitlements
+        .route("/plans", get(entitlements::list_plans_handler))
+        .route("/entitlements", get(entitlements::get_entitlements_handler))
+        .route("/admin/tenants/:id/plan", get(entitlements::get_tenant_plan_handler))
+        .route("/admin/tenants/:id/plan", put(entitlements::set_tenant_plan_handler).layer(limits.layer("/admin/tenants/:id/plan")))
+        // Data retention
+        .route("/admin/tenants/:id/retention", get(retention::get_retention_handler))
+        .route("/admin/tenants/:id/retention/preview", get(retention::preview_retention_handler))
+        .route("/admin/tenants/:id/retention/executions", get(retention::list_purge_executions_handler))
+        .route("/admin/tenants/:id/retention/:category", put(retention::set_retention_policy_handler).layer(limits.layer("/admin/tenants/:id/retention/:category")))
+        .route("/admin/tenants/:id/retention/:category", delete(retention::reset_retention_policy_handler))
+        // Remote commands on fleet instances
+        .route("/fleets/:fleet_id/groups/:group_id/commands", post(commands::run_command_handler).layer(limits.layer("/fleets/:fleet_id/groups/:group_id/commands")))
+        .route("/commands/:id", get(commands::get_command_handler))
+        // SSH sessions on fleet instances
+        .route("/fleets/:fleet_id/groups/:group_id/instances/:instance_id/ssh-sessions", post(bastion::start_session_handler).layer(limits.layer("/fleets/:fleet_id/groups/:group_id/instances/:instance_id/ssh-sessions")))
+        .route("/ssh-sessions/:id/attach", get(bastion::attach_session_handler))
+        .route("/ssh/ca", get(bastion::ssh_ca_handler))
+        .route("/admin/ssh-sessions", get(bastion::list_sessions_handler))
+        .route("/admin/ssh-sessions/:id/terminate", post(bastion::terminate_session_handler))
+        .route("/admin/ssh-sessions/:id/recording", get(bastion::session_recording_handler))
+        // Feature flags
+        .route("/admin/flags", get(flags::list_flags_handler))
+        .route("/admin/flags", post(flags::create_flag_handler).layer(limits.layer("/admin/flags")))
+        .route("/admin/flags/:key", get(flags::get_flag_handler))
+        .route("/admin/flags/:key", put(flags::update_flag_handler).layer(limits.layer("/admin/flags/:key")))
+        .route("/admin/flags/:key", delete(flags::delete_flag_handler))
+        // Impersonation routes
+        .route("/admin/users/:id/unlock", post(auth::admin_unlock_handler))
+        .route("/admin/impersonation", get(impersonation::list_started_impersonations_handler))
+        .route("/admin/impersonation", post(impersonation::start_impersonation_handler).layer(limits.layer("/admin/impersonation")))
+        .route("/impersonation/sessions", get(impersonation::list_impersonation_sessions_handler))
+        .route("/impersonation/sessions/:id", delete(impersonation::revoke_impersonation_handler))
+        // Privacy requests
+        .route("/privacy/export-requests", post(privacy::create_export_request_handler).layer(limits.layer("/privacy/export-requests")))
+        .route("/privacy/erasure-requests", post(privacy::create_erasure_request_handler).layer(limits.layer("/privacy/erasure-requests")))
+        .route("/privacy/requests/:id", get(privacy::get_privacy_request_handler))
+        .route("/admin/privacy/requests", get(privacy::list_privacy_requests_handler))
+        .route("/admin/privacy/requests/:id/approve", post(privacy::approve_privacy_request_handler).layer(limits.layer("/admin/privacy/requests/:id/approve")))
+        .route("/admin/privacy/requests/:id/reject", post(privacy::reject_privacy_request_handler).layer(limits.layer("/admin/privacy/requests/:id/reject")))
+        // Budgets
+        .route("/budgets", get(budgets::list_budgets_handler))
+        .route("/budgets", post(budgets::create_budget_handler).layer(limits.layer("/budgets")))
+        .route("/budgets/:id", get(budgets::get_budget_handler))
+        .route("/budgets/:id", delete(budgets::delete_budget_handler))
+        .route("/budgets/:id/status", get(budgets::get_budget_status_handler))
+        .route("/budgets/:id/enforcements", get(budgets::list_budget_enforcements_handler))
+        .route("/admin/tenants/:id/budget-enforcements", get(budgets::admin_list_enforcements_handler))
+        .route("/admin/budget-enforcements/:id/override", post(budgets::override_enforcement_handler).layer(limits.layer("/admin/budget-enforcements/:id/override")))
+        // Function code uploads
+        .route(FUNCTION_CODE_ROUTE, post(functions::upload_function_code_handler))
+        .layer(DefaultBodyLimit::max(limits.default_limit()))
+        .layer(Extension(services.exports))
+        .layer(Extension(services.api_keys))
+        .layer(Extension(services.function_code))
+        .layer(Extension(services.metering))
+        .layer(Extension(services.entitlements))
+        .layer(Extension(services.discovery))
+        .layer(Extension(services.inventory))
+        .layer(Extension(services.compliance))
+        .layer(Extension(services.remediation))
+        .layer(Extension(services.provider_events))
+        .layer(Extension(services.reports))
+        .layer(Extension(services.flags))
+        .layer(Extension(services.impersonation))
+        .layer(Extension(services.orgs))
+        .layer(Extension(services.devices))
+        .layer(Extension(services.passwords))
+        .layer(Extension(services.webhooks))
+        .layer(Extension(services.retention))
+        .layer(Extension(services.privacy))
+        .layer(Extension(services.budgets))
+        .layer(Extension(services.workflow_runs))
+        .layer(Extension(limits));
+    let router = match services.commands {
+        Some(runner) => router.layer(Extension(runner)),
+        None => router,
+    };
+    let router = match services.bastion {
+        Some(broker) => router.layer(Extension(broker)),
+        None => router,
+    };
+    match services.warehouse {
+        Some(warehouse) => router.layer(Extension(warehouse)).with_state(db),
+        None => router.with_state(db),
+    }
 }
 
 #[cfg(test)]
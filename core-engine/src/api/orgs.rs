@@ -0,0 +1,198 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    db::DbPool,
+    error::AppResult,
+    metering::{MeteringService, TenantUsage},
+    middleware::{AuthUser, Scope},
+    orgs::{OrgInvitation, OrgMembership, OrgPermission, OrgRole, OrgService, Organization},
+};
+
+use super::audit::record_audit;
+use super::usage::UsageParams;
+
+#[derive(Debug, Deserialize)]
+pub struct OrgRequest {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MemberRoleRequest {
+    pub role: OrgRole,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InvitationRequest {
+    pub email: String,
+    pub role: OrgRole,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AcceptInvitationRequest {
+    pub token: String,
+}
+
+// The token goes out in the invitation email and is never retrievable again
+#[derive(Debug, Serialize)]
+pub struct IssuedInvitationResponse {
+    #[serde(flatten)]
+    pub invitation: OrgInvitation,
+    pub token: String,
+}
+
+#[axum::debug_handler]
+pub async fn create_org_handler(
+    State(db): State<DbPool>,
+    Extension(orgs): Extension<Arc<OrgService>>,
+    auth: AuthUser,
+    Json(payload): Json<OrgRequest>,
+) -> AppResult<(StatusCode, Json<Organization>)> {
+    auth.require(Scope::ManageOrgs)?;
+    let org = orgs.create(&payload.name, auth.user_id, Utc::now()).await?;
+    record_audit(&db, &auth, "org.create", Some(org.id), json!({ "name": org.name })).await;
+    Ok((StatusCode::CREATED, Json(org)))
+}
+
+#[axum::debug_handler(state = DbPool)]
+pub async fn list_orgs_handler(
+    Extension(orgs): Extension<Arc<OrgService>>,
+    auth: AuthUser,
+) -> AppResult<Json<Vec<Organization>>> {
+    auth.require(Scope::ReadProjects)?;
+    Ok(Json(orgs.list_for_user(auth.user_id).await?))
+}
+
+#[axum::debug_handler(state = DbPool)]
+pub async fn get_org_handler(
+    Extension(orgs): Extension<Arc<OrgService>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<Organization>> {
+    auth.require(Scope::ReadProjects)?;
+    orgs.authorize(auth.user_id, id, OrgPermission::ReadProjects).await?;
+    Ok(Json(orgs.get(id).await?))
+}
+
+#[axum::debug_handler]
+pub async fn update_org_handler(
+    State(db): State<DbPool>,
+    Extension(orgs): Extension<Arc<OrgService>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<OrgRequest>,
+) -> AppResult<Json<Organization>> {
+    auth.require(Scope::ManageOrgs)?;
+    let org = orgs.rename(auth.user_id, id, &payload.name).await?;
+    record_audit(&db, &auth, "org.update", Some(id), json!({ "name": org.name })).await;
+    Ok(Json(org))
+}
+
+#[axum::debug_handler]
+pub async fn delete_org_handler(
+    State(db): State<DbPool>,
+    Extension(orgs): Extension<Arc<OrgService>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> AppResult<StatusCode> {
+    auth.require(Scope::ManageOrgs)?;
+    auth.require_direct("Deleting an organization")?;
+    let org = orgs.delete(auth.user_id, id).await?;
+    record_audit(&db, &auth, "org.delete", Some(id), json!({ "name": org.name })).await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[axum::debug_handler(state = DbPool)]
+pub async fn list_members_handler(
+    Extension(orgs): Extension<Arc<OrgService>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<Vec<OrgMembership>>> {
+    auth.require(Scope::ReadProjects)?;
+    Ok(Json(orgs.members(auth.user_id, id).await?))
+}
+
+#[axum::debug_handler]
+pub async fn set_member_role_handler(
+    State(db): State<DbPool>,
+    Extension(orgs): Extension<Arc<OrgService>>,
+    auth: AuthUser,
+    Path((id, user_id)): Path<(Uuid, Uuid)>,
+    Json(payload): Json<MemberRoleRequest>,
+) -> AppResult<Json<OrgMembership>> {
+    auth.require(Scope::ManageOrgs)?;
+    let membership = orgs.set_role(auth.user_id, id, user_id, payload.role).await?;
+    record_audit(&db, &auth, "org.member.update", Some(id), json!({ "user_id": user_id, "role": membership.role })).await;
+    Ok(Json(membership))
+}
+
+#[axum::debug_handler]
+pub async fn remove_member_handler(
+    State(db): State<DbPool>,
+    Extension(orgs): Extension<Arc<OrgService>>,
+    auth: AuthUser,
+    Path((id, user_id)): Path<(Uuid, Uuid)>,
+) -> AppResult<StatusCode> {
+    auth.require(Scope::ManageOrgs)?;
+    let membership = orgs.remove_member(auth.user_id, id, user_id).await?;
+    record_audit(&db, &auth, "org.member.remove", Some(id), json!({ "user_id": user_id, "role": membership.role })).await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[axum::debug_handler]
+pub async fn create_invitation_handler(
+    State(db): State<DbPool>,
+    Extension(orgs): Extension<Arc<OrgService>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<InvitationRequest>,
+) -> AppResult<(StatusCode, Json<IssuedInvitationResponse>)> {
+    auth.require(Scope::ManageOrgs)?;
+    let (invitation, token) = orgs.invite(auth.user_id, id, &payload.email, payload.role, Utc::now()).await?;
+    record_audit(
+        &db,
+        &auth,
+        "org.invite",
+        Some(id),
+        json!({ "invitation_id": invitation.id, "email": invitation.email, "role": invitation.role, "expires_at": invitation.expires_at }),
+    )
+    .await;
+    Ok((StatusCode::CREATED, Json(IssuedInvitationResponse { invitation, token })))
+}
+
+#[axum::debug_handler]
+pub async fn accept_invitation_handler(
+    State(db): State<DbPool>,
+    Extension(orgs): Extension<Arc<OrgService>>,
+    auth: AuthUser,
+    Json(payload): Json<AcceptInvitationRequest>,
+) -> AppResult<Json<OrgMembership>> {
+    auth.require_direct("Accepting an invitation")?;
+    let membership = orgs.accept(auth.user_id, &auth.user.email, &payload.token, Utc::now()).await?;
+    record_audit(&db, &auth, "org.join", Some(membership.org_id), json!({ "role": membership.role })).await;
+    Ok(Json(membership))
+}
+
+// Billing view of the org as a tenant
+#[axum::debug_handler(state = DbPool)]
+pub async fn org_usage_handler(
+    Extension(orgs): Extension<Arc<OrgService>>,
+    Extension(metering): Extension<Arc<MeteringService>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+    Query(params): Query<UsageParams>,
+) -> AppResult<Json<TenantUsage>> {
+    auth.require(Scope::ReadProjects)?;
+    orgs.authorize(auth.user_id, id, OrgPermission::ManageBilling).await?;
+    let (since, until) = params.range()?;
+    Ok(Json(metering.usage(id, since, until).await?))
+}
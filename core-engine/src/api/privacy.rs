@@ -0,0 +1,142 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    db::DbPool,
+    error::{AppError, AppResult},
+    middleware::{AuthUser, Scope},
+    privacy::{PrivacyRequest, PrivacyRequestKind, PrivacyRequestResponse, PrivacyRequestStatus, PrivacyService},
+};
+
+use super::audit::record_audit;
+
+const DEFAULT_LIMIT: i64 = 50;
+const MAX_LIMIT: i64 = 500;
+
+// Users file requests about themselves; naming someone else takes tenant management rights
+#[derive(Debug, Deserialize)]
+pub struct PrivacyRequestBody {
+    pub user_id: Option<Uuid>,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReviewRequest {
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListRequestsParams {
+    pub status: Option<PrivacyRequestStatus>,
+    pub limit: Option<i64>,
+}
+
+async fn file_request(
+    db: &DbPool,
+    privacy: &PrivacyService,
+    auth: &AuthUser,
+    kind: PrivacyRequestKind,
+    payload: PrivacyRequestBody,
+) -> AppResult<(StatusCode, Json<PrivacyRequest>)> {
+    let subject_id = payload.user_id.unwrap_or(auth.user_id);
+    if subject_id != auth.user_id {
+        auth.require(Scope::ManageTenants)?;
+    }
+    let request = privacy.request(kind, subject_id, auth.actor_id(), payload.reason, Utc::now()).await?;
+    let action = format!("privacy.{}_request", kind.as_str());
+    record_audit(db, auth, &action, Some(request.id), json!({ "subject_id": subject_id })).await;
+    Ok((StatusCode::ACCEPTED, Json(request)))
+}
+
+#[axum::debug_handler]
+pub async fn create_export_request_handler(
+    State(db): State<DbPool>,
+    Extension(privacy): Extension<Arc<PrivacyService>>,
+    auth: AuthUser,
+    Json(payload): Json<PrivacyRequestBody>,
+) -> AppResult<(StatusCode, Json<PrivacyRequest>)> {
+    file_request(&db, &privacy, &auth, PrivacyRequestKind::Export, payload).await
+}
+
+#[axum::debug_handler]
+pub async fn create_erasure_request_handler(
+    State(db): State<DbPool>,
+    Extension(privacy): Extension<Arc<PrivacyService>>,
+    auth: AuthUser,
+    Json(payload): Json<PrivacyRequestBody>,
+) -> AppResult<(StatusCode, Json<PrivacyRequest>)> {
+    auth.require_direct("Requesting erasure")?;
+    file_request(&db, &privacy, &auth, PrivacyRequestKind::Erasure, payload).await
+}
+
+// Visible to the subject, whoever filed it, and tenant managers
+#[axum::debug_handler]
+pub async fn get_privacy_request_handler(
+    State(db): State<DbPool>,
+    Extension(privacy): Extension<Arc<PrivacyService>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<PrivacyRequestResponse>> {
+    let response = privacy.get(id).await?;
+    let request = &response.request;
+    let involved = request.subject_id == auth.user_id || request.requested_by == auth.actor_id();
+    if !involved && auth.require(Scope::ManageTenants).is_err() {
+        return Err(AppError::NotFound("Privacy request not found".into()));
+    }
+    if response.download_url.is_some() {
+        record_audit(&db, &auth, "privacy.export_download", Some(id), json!({ "subject_id": request.subject_id })).await;
+    }
+    Ok(Json(response))
+}
+
+#[axum::debug_handler(state = DbPool)]
+pub async fn list_privacy_requests_handler(
+    Extension(privacy): Extension<Arc<PrivacyService>>,
+    auth: AuthUser,
+    Query(params): Query<ListRequestsParams>,
+) -> AppResult<Json<Vec<PrivacyRequest>>> {
+    auth.require(Scope::ManageTenants)?;
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    Ok(Json(privacy.list(params.status, limit).await?))
+}
+
+#[axum::debug_handler]
+pub async fn approve_privacy_request_handler(
+    State(db): State<DbPool>,
+    Extension(privacy): Extension<Arc<PrivacyService>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<ReviewRequest>,
+) -> AppResult<Json<PrivacyRequest>> {
+    auth.require(Scope::ManageTenants)?;
+    auth.require_direct("Approving a privacy request")?;
+    let request = privacy.approve(id, auth.user_id, payload.note, Utc::now()).await?;
+    let action = format!("privacy.{}_approve", request.kind.as_str());
+    record_audit(&db, &auth, &action, Some(id), json!({ "subject_id": request.subject_id, "note": request.review_note })).await;
+    Ok(Json(request))
+}
+
+#[axum::debug_handler]
+pub async fn reject_privacy_request_handler(
+    State(db): State<DbPool>,
+    Extension(privacy): Extension<Arc<PrivacyService>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<ReviewRequest>,
+) -> AppResult<Json<PrivacyRequest>> {
+    auth.require(Scope::ManageTenants)?;
+    auth.require_direct("Rejecting a privacy request")?;
+    let request = privacy.reject(id, auth.user_id, payload.note, Utc::now()).await?;
+    let action = format!("privacy.{}_reject", request.kind.as_str());
+    record_audit(&db, &auth, &action, Some(id), json!({ "subject_id": request.subject_id, "note": request.review_note })).await;
+    Ok(Json(request))
+}
@@ -1,23 +1,35 @@
+use std::sync::Arc;
+
 use axum::{
     extract::{Path, State},
-    Json,
+    http::{header, HeaderMap, HeaderName},
+    Extension, Json,
 };
+use chrono::Utc;
 use serde::Deserialize;
+use serde_json::json;
 use sqlx::PgPool; // CockroachDB uses PostgreSQL protocol
 use uuid::Uuid;
 use validator::Validate;
 
 use crate::{
-    error::AppResult,
-    middleware::AuthUser,
+    error::{AppError, AppResult},
+    metering::{MeteringService, Quota},
+    middleware::{AuthUser, Scope},
     models::project::{CreateProject, Project, ProjectStatus},
+    models::version::{etag, expected_version, settle_update},
+    orgs::{OrgPermission, OrgService},
 };
 
+use super::audit::record_audit;
+
 #[derive(Debug, Deserialize, Validate)]
 pub struct CreateProjectRequest {
     #[validate(length(min = 1, max = 255))]
     pub name: String,
     pub description: Option<String>,
+    // Defaults to the caller's personal organization
+    pub org_id: Option<Uuid>,
 }
 
 #[derive(Debug, Deserialize, Validate)]
@@ -26,6 +38,28 @@ pub struct UpdateProjectRequest {
     pub name: Option<String>,
     pub description: Option<String>,
     pub status: Option<ProjectStatus>,
+    // Alternative to If-Match for clients that can't set headers
+    pub version: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TransferProjectRequest {
+    pub org_id: Uuid,
+}
+
+// Looks the project up and checks the caller's org role against it
+pub(super) async fn authorized_project(
+    pool: &PgPool,
+    orgs: &OrgService,
+    auth: &AuthUser,
+    id: Uuid,
+    permission: OrgPermission,
+) -> AppResult<Project> {
+    let project = Project::find_by_id(pool, id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Project not found".into()))?;
+    orgs.authorize_project(auth.user_id, (&project).into(), permission).await?;
+    Ok(project)
 }
 
 #[axum::debug_handler]
@@ -33,28 +67,48 @@ pub async fn list_projects_handler(
     State(pool): State<PgPool>,
     auth: AuthUser,
 ) -> AppResult<Json<Vec<Project>>> {
-    let projects = Project::find_by_owner(&pool, auth.user.id).await?;
+    auth.require(Scope::ReadProjects)?;
+    let projects = Project::find_for_member(&pool, auth.user.id).await?;
     Ok(Json(projects))
 }
 
 #[axum::debug_handler]
 pub async fn create_project_handler(
     State(pool): State<PgPool>,
+    Extension(metering): Extension<Arc<MeteringService>>,
+    Extension(orgs): Extension<Arc<OrgService>>,
     auth: AuthUser,
     Json(payload): Json<CreateProjectRequest>,
 ) -> AppResult<Json<Project>> {
+    auth.require(Scope::WriteProjects)?;
+
     // Validate request
     payload.validate()?;
+    let org_id = match payload.org_id {
+        Some(org_id) => orgs.authorize(auth.user_id, org_id, OrgPermission::WriteProjects).await?.org_id,
+        None => orgs.personal_org(auth.user_id, &auth.user.name, Utc::now()).await?.id,
+    };
 
-    // Create project
-    let project = Project::create(
+    // Reserve quota first so concurrent creates can't overshoot; hand it back if the insert fails
+    let counted = metering.acquire(org_id, Quota::MaxProjects, None).await?;
+    let created = Project::create(
         &pool,
         CreateProject {
             name: payload.name,
             description: payload.description,
             owner_id: auth.user.id,
+            org_id,
         },
-    ).await?;
+    ).await;
+    let project = match created {
+        Ok(project) => project,
+        Err(e) => {
+            if counted {
+                metering.release(org_id, Quota::MaxProjects, None).await?;
+            }
+            return Err(e);
+        }
+    };
 
     Ok(Json(project))
 }
@@ -62,40 +116,33 @@ pub async fn create_project_handler(
 #[axum::debug_handler]
 pub async fn get_project_handler(
     State(pool): State<PgPool>,
+    Extension(orgs): Extension<Arc<OrgService>>,
     auth: AuthUser,
     Path(id): Path<Uuid>,
-) -> AppResult<Json<Project>> {
-    let project = Project::find_by_id(&pool, id)
-        .await?
-        .ok_or_else(|| crate::error::AppError::NotFound("Project not found".into()))?;
-
-    // Verify ownership
-    if project.owner_id != auth.user.id {
-        return Err(crate::error::AppError::Auth("Not authorized".into()));
-    }
+) -> AppResult<([(HeaderName, String); 1], Json<Project>)> {
+    auth.require(Scope::ReadProjects)?;
+    let project = authorized_project(&pool, &orgs, &auth, id, OrgPermission::ReadProjects).await?;
 
-    Ok(Json(project))
+    Ok(([(header::ETAG, etag(project.version))], Json(project)))
 }
 
 #[axum::debug_handler]
 pub async fn update_project_handler(
     State(pool): State<PgPool>,
+    Extension(orgs): Extension<Arc<OrgService>>,
     auth: AuthUser,
     Path(id): Path<Uuid>,
+    headers: HeaderMap,
     Json(payload): Json<UpdateProjectRequest>,
-) -> AppResult<Json<Project>> {
+) -> AppResult<([(HeaderName, String); 1], Json<Project>)> {
+    auth.require(Scope::WriteProjects)?;
+
     // Validate request
     payload.validate()?;
+    let if_match = headers.get(header::IF_MATCH).and_then(|value| value.to_str().ok());
+    let expected = expected_version(if_match, payload.version)?;
 
-    // Get project
-    let mut project = Project::find_by_id(&pool, id)
-        .await?
-        .ok_or_else(|| crate::error::AppError::NotFound("Project not found".into()))?;
-
-    // Verify ownership
-    if project.owner_id != auth.user.id {
-        return Err(crate::error::AppError::Auth("Not authorized".into()));
-    }
+    let mut project = authorized_project(&pool, &orgs, &auth, id, OrgPermission::WriteProjects).await?;
 
     // Update project
     if let Some(name) = payload.name {
@@ -108,33 +155,76 @@ pub async fn update_project_handler(
         project.status = status;
     }
 
-    project.update(&pool).await?;
+    // The version guard in the UPDATE makes the check atomic; a stale write comes back as a
+    // 409 carrying whatever the winner saved
+    project.version = expected;
+    let updated = project.update(&pool).await?;
+    let project = settle_update("Project", updated, Project::find_by_id(&pool, id)).await?;
 
-    Ok(Json(project))
+    Ok(([(header::ETAG, etag(project.version))], Json(project)))
 }
 
 #[axum::debug_handler]
 pub async fn delete_project_handler(
     State(pool): State<PgPool>,
+    Extension(metering): Extension<Arc<MeteringService>>,
+    Extension(orgs): Extension<Arc<OrgService>>,
     auth: AuthUser,
     Path(id): Path<Uuid>,
 ) -> AppResult<()> {
-    // Get project
-    let project = Project::find_by_id(&pool, id)
-        .await?
-        .ok_or_else(|| crate::error::AppError::NotFound("Project not found".into()))?;
+    auth.require(Scope::WriteProjects)?;
+    auth.require_direct("Deleting a project")?;
 
-    // Verify ownership
-    if project.owner_id != auth.user.id {
-        return Err(crate::error::AppError::Auth("Not authorized".into()));
-    }
+    let project = authorized_project(&pool, &orgs, &auth, id, OrgPermission::DeleteProjects).await?;
 
     // Delete project
     Project::delete(&pool, id).await?;
+    metering.release(project.org_id, Quota::MaxProjects, None).await?;
 
     Ok(())
 }
 
+// The project's quota moves with it: reserved in the destination first, released from the
+// source once the move has happened
+#[axum::debug_handler]
+pub async fn transfer_project_handler(
+    State(pool): State<PgPool>,
+    Extension(metering): Extension<Arc<MeteringService>>,
+    Extension(orgs): Extension<Arc<OrgService>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<TransferProjectRequest>,
+) -> AppResult<Json<Project>> {
+    auth.require(Scope::WriteProjects)?;
+    auth.require_direct("Transferring a project")?;
+    let project = authorized_project(&pool, &orgs, &auth, id, OrgPermission::TransferProjects).await?;
+
+    let counted = metering.acquire(payload.org_id, Quota::MaxProjects, None).await?;
+    let moved = match orgs.transfer_project(auth.user_id, (&project).into(), payload.org_id).await {
+        Ok(moved) => moved,
+        Err(e) => {
+            if counted {
+                metering.release(payload.org_id, Quota::MaxProjects, None).await?;
+            }
+            return Err(e);
+        }
+    };
+    metering.release(project.org_id, Quota::MaxProjects, None).await?;
+    record_audit(
+        &pool,
+        &auth,
+        "project.transfer",
+        Some(id),
+        json!({ "from_org_id": project.org_id, "to_org_id": moved.org_id, "previous_owner_id": project.owner_id }),
+    )
+    .await;
+
+    let project = Project::find_by_id(&pool, id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Project not found".into()))?;
+    Ok(Json(project))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -177,6 +267,7 @@ use sqlx::postgres::PgPoolOptions; // CockroachDB connection
         let request = CreateProjectRequest {
             name: "Test Project".to_string(),
             description: Some("Test Description".to_string()),
+            org_id: None,
         };
 
         // TODO: Add test with authentication once auth is implemented
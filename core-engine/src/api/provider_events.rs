@@ -0,0 +1,114 @@
+use std::sync::Arc;
+
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    Extension, Json,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    db::DbPool,
+    error::AppResult,
+    middleware::{AuthUser, Scope},
+    provider_events::{
+        EventOutcome, EventSource, NewEventSource, ProviderEventService, UnmappedEvent, UnmappedType, MESSAGE_TYPE_HEADER,
+        TOKEN_HEADER,
+    },
+};
+
+use super::audit::record_audit;
+
+const DEFAULT_LIMIT: i64 = 50;
+const MAX_LIMIT: i64 = 500;
+
+#[derive(Debug, Serialize)]
+pub struct CreatedEventSourceResponse {
+    #[serde(flatten)]
+    pub source: EventSource,
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UnmappedEventParams {
+    pub event_type: String,
+    pub limit: Option<i64>,
+}
+
+// Called by AWS, not users: EventBridge API destinations authenticate with the source's token
+// and SNS deliveries with their signature
+#[axum::debug_handler(state = DbPool)]
+pub async fn receive_aws_event_handler(
+    Extension(events): Extension<Arc<ProviderEventService>>,
+    Path(source_id): Path<Uuid>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> AppResult<Json<EventOutcome>> {
+    let outcome = if headers.contains_key(MESSAGE_TYPE_HEADER) {
+        events.receive_sns(source_id, &body, Utc::now()).await?
+    } else {
+        let token = headers.get(TOKEN_HEADER).and_then(|v| v.to_str().ok());
+        events.receive_direct(source_id, token, &body, Utc::now()).await?
+    };
+    Ok(Json(outcome))
+}
+
+#[axum::debug_handler]
+pub async fn create_event_source_handler(
+    State(db): State<DbPool>,
+    Extension(events): Extension<Arc<ProviderEventService>>,
+    auth: AuthUser,
+    Json(payload): Json<NewEventSource>,
+) -> AppResult<(StatusCode, Json<CreatedEventSourceResponse>)> {
+    auth.require(Scope::ManageWebhooks)?;
+    let (source, token) = events.create_source(auth.user_id, payload, Utc::now()).await?;
+    record_audit(&db, &auth, "event_source.create", Some(source.id), json!({ "topic_arns": source.topic_arns })).await;
+    Ok((StatusCode::CREATED, Json(CreatedEventSourceResponse { source, token })))
+}
+
+#[axum::debug_handler(state = DbPool)]
+pub async fn list_event_sources_handler(
+    Extension(events): Extension<Arc<ProviderEventService>>,
+    auth: AuthUser,
+) -> AppResult<Json<Vec<EventSource>>> {
+    auth.require(Scope::ManageWebhooks)?;
+    Ok(Json(events.sources(auth.user_id).await?))
+}
+
+#[axum::debug_handler]
+pub async fn delete_event_source_handler(
+    State(db): State<DbPool>,
+    Extension(events): Extension<Arc<ProviderEventService>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> AppResult<StatusCode> {
+    auth.require(Scope::ManageWebhooks)?;
+    events.delete_source(auth.user_id, id).await?;
+    record_audit(&db, &auth, "event_source.delete", Some(id), json!({})).await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Event types received without a mapping, most frequent first
+#[axum::debug_handler(state = DbPool)]
+pub async fn list_unmapped_event_types_handler(
+    Extension(events): Extension<Arc<ProviderEventService>>,
+    auth: AuthUser,
+) -> AppResult<Json<Vec<UnmappedType>>> {
+    auth.require(Scope::ReadResources)?;
+    Ok(Json(events.unmapped_types(auth.user_id).await?))
+}
+
+#[axum::debug_handler(state = DbPool)]
+pub async fn list_unmapped_events_handler(
+    Extension(events): Extension<Arc<ProviderEventService>>,
+    auth: AuthUser,
+    Query(params): Query<UnmappedEventParams>,
+) -> AppResult<Json<Vec<UnmappedEvent>>> {
+    auth.require(Scope::ReadResources)?;
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    Ok(Json(events.unmapped_events(auth.user_id, &params.event_type, limit).await?))
+}
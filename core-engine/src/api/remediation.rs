@@ -0,0 +1,134 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    db::DbPool,
+    error::AppResult,
+    middleware::{AuthUser, Scope},
+    remediation::{MappingUpdate, NewRemediationMapping, Remediation, RemediationDetail, RemediationMapping, RemediationService},
+};
+
+use super::audit::record_audit;
+
+const DEFAULT_LIMIT: i64 = 100;
+const MAX_LIMIT: i64 = 1000;
+
+#[derive(Debug, Deserialize)]
+pub struct RemediateRequest {
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RemediationParams {
+    pub finding_id: Option<Uuid>,
+    pub limit: Option<i64>,
+}
+
+#[axum::debug_handler(state = DbPool)]
+pub async fn list_remediation_mappings_handler(
+    Extension(remediation): Extension<Arc<RemediationService>>,
+    auth: AuthUser,
+) -> AppResult<Json<Vec<RemediationMapping>>> {
+    auth.require(Scope::ReadResources)?;
+    Ok(Json(remediation.mappings(auth.user_id).await?))
+}
+
+#[axum::debug_handler]
+pub async fn create_remediation_mapping_handler(
+    State(db): State<DbPool>,
+    Extension(remediation): Extension<Arc<RemediationService>>,
+    auth: AuthUser,
+    Json(mapping): Json<NewRemediationMapping>,
+) -> AppResult<(StatusCode, Json<RemediationMapping>)> {
+    auth.require(Scope::WriteResources)?;
+    let mapping = remediation.create_mapping(auth.user_id, mapping, auth.actor_id(), Utc::now()).await?;
+    record_audit(&db, &auth, "remediation.mapping_create", Some(mapping.id), json!({
+        "rule_key": mapping.rule_key,
+        "template_id": mapping.template_id,
+        "auto_remediate": mapping.auto_remediate,
+        "max_concurrent": mapping.max_concurrent,
+    })).await;
+    Ok((StatusCode::CREATED, Json(mapping)))
+}
+
+#[axum::debug_handler]
+pub async fn update_remediation_mapping_handler(
+    State(db): State<DbPool>,
+    Extension(remediation): Extension<Arc<RemediationService>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+    Json(update): Json<MappingUpdate>,
+) -> AppResult<Json<RemediationMapping>> {
+    auth.require(Scope::WriteResources)?;
+    let mapping = remediation.update_mapping(auth.user_id, id, update).await?;
+    record_audit(&db, &auth, "remediation.mapping_update", Some(mapping.id), json!({
+        "auto_remediate": mapping.auto_remediate,
+        "max_concurrent": mapping.max_concurrent,
+    })).await;
+    Ok(Json(mapping))
+}
+
+#[axum::debug_handler]
+pub async fn delete_remediation_mapping_handler(
+    State(db): State<DbPool>,
+    Extension(remediation): Extension<Arc<RemediationService>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> AppResult<StatusCode> {
+    auth.require(Scope::WriteResources)?;
+    remediation.delete_mapping(auth.user_id, id).await?;
+    record_audit(&db, &auth, "remediation.mapping_delete", Some(id), json!({})).await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Starts the rule's mapped workflow for one finding; the run is verified by the worker
+#[axum::debug_handler]
+pub async fn remediate_finding_handler(
+    State(db): State<DbPool>,
+    Extension(remediation): Extension<Arc<RemediationService>>,
+    auth: AuthUser,
+    Path(finding_id): Path<Uuid>,
+    Json(payload): Json<RemediateRequest>,
+) -> AppResult<(StatusCode, Json<Remediation>)> {
+    auth.require(Scope::WriteResources)?;
+    let reason = payload.reason;
+    let run = remediation.remediate(auth.user_id, finding_id, auth.actor_id(), reason.clone(), Utc::now()).await?;
+    record_audit(&db, &auth, "remediation.trigger", Some(run.id), json!({
+        "finding_id": finding_id,
+        "rule_key": run.rule_key,
+        "template_id": run.template_id,
+        "reason": reason,
+    })).await;
+    Ok((StatusCode::ACCEPTED, Json(run)))
+}
+
+#[axum::debug_handler(state = DbPool)]
+pub async fn list_remediations_handler(
+    Extension(remediation): Extension<Arc<RemediationService>>,
+    auth: AuthUser,
+    Query(params): Query<RemediationParams>,
+) -> AppResult<Json<Vec<Remediation>>> {
+    auth.require(Scope::ReadResources)?;
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    Ok(Json(remediation.remediations(auth.user_id, params.finding_id, limit).await?))
+}
+
+// The remediation with its event trail
+#[axum::debug_handler(state = DbPool)]
+pub async fn get_remediation_handler(
+    Extension(remediation): Extension<Arc<RemediationService>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<RemediationDetail>> {
+    auth.require(Scope::ReadResources)?;
+    Ok(Json(remediation.remediation(auth.user_id, id).await?))
+}
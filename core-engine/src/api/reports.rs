@@ -0,0 +1,132 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    db::DbPool,
+    error::{AppError, AppResult},
+    middleware::{AuthUser, Scope},
+    models::project::Project,
+    orgs::{OrgPermission, OrgService},
+    reporting::{NewReport, ReportDefinition, ReportRun, ReportService},
+};
+
+use super::audit::record_audit;
+
+const DEFAULT_LIMIT: i64 = 20;
+const MAX_LIMIT: i64 = 200;
+
+#[derive(Debug, Deserialize)]
+pub struct ListRunsParams {
+    pub limit: Option<i64>,
+}
+
+#[axum::debug_handler]
+pub async fn create_report_handler(
+    State(db): State<DbPool>,
+    Extension(reports): Extension<Arc<ReportService>>,
+    Extension(orgs): Extension<Arc<OrgService>>,
+    auth: AuthUser,
+    Json(report): Json<NewReport>,
+) -> AppResult<(StatusCode, Json<ReportDefinition>)> {
+    auth.require(Scope::WriteResources)?;
+    if let Some(project_id) = report.project_id {
+        let project = Project::find_by_id(&db, project_id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Project not found".into()))?;
+        orgs.authorize_project(auth.user_id, (&project).into(), OrgPermission::ReadProjects).await?;
+    }
+    let report = reports.create(auth.user_id, report, Utc::now()).await?;
+    record_audit(&db, &auth, "report.create", Some(report.id), json!({ "name": report.name })).await;
+    Ok((StatusCode::CREATED, Json(report)))
+}
+
+#[axum::debug_handler(state = DbPool)]
+pub async fn list_reports_handler(
+    Extension(reports): Extension<Arc<ReportService>>,
+    auth: AuthUser,
+) -> AppResult<Json<Vec<ReportDefinition>>> {
+    auth.require(Scope::ReadResources)?;
+    Ok(Json(reports.reports(auth.user_id).await?))
+}
+
+#[axum::debug_handler(state = DbPool)]
+pub async fn get_report_handler(
+    Extension(reports): Extension<Arc<ReportService>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<ReportDefinition>> {
+    auth.require(Scope::ReadResources)?;
+    Ok(Json(reports.report(auth.user_id, id).await?))
+}
+
+#[axum::debug_handler]
+pub async fn delete_report_handler(
+    State(db): State<DbPool>,
+    Extension(reports): Extension<Arc<ReportService>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> AppResult<StatusCode> {
+    auth.require(Scope::WriteResources)?;
+    reports.delete(auth.user_id, id).await?;
+    record_audit(&db, &auth, "report.delete", Some(id), json!({})).await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Renders and delivers the report for the past week straight away
+#[axum::debug_handler(state = DbPool)]
+pub async fn run_report_handler(
+    Extension(reports): Extension<Arc<ReportService>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> AppResult<(StatusCode, Json<ReportRun>)> {
+    auth.require(Scope::WriteResources)?;
+    Ok((StatusCode::CREATED, Json(reports.run_now(auth.user_id, id, Utc::now()).await?)))
+}
+
+#[axum::debug_handler(state = DbPool)]
+pub async fn list_report_runs_handler(
+    Extension(reports): Extension<Arc<ReportService>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+    Query(params): Query<ListRunsParams>,
+) -> AppResult<Json<Vec<ReportRun>>> {
+    auth.require(Scope::ReadResources)?;
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    Ok(Json(reports.runs(auth.user_id, id, limit).await?))
+}
+
+#[axum::debug_handler(state = DbPool)]
+pub async fn get_report_run_handler(
+    Extension(reports): Extension<Arc<ReportService>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<ReportRun>> {
+    auth.require(Scope::ReadResources)?;
+    Ok(Json(reports.run(auth.user_id, id).await?))
+}
+
+#[axum::debug_handler(state = DbPool)]
+pub async fn download_report_artifact_handler(
+    Extension(reports): Extension<Arc<ReportService>>,
+    auth: AuthUser,
+    Path((id, name)): Path<(Uuid, String)>,
+) -> AppResult<Response> {
+    auth.require(Scope::ReadResources)?;
+    let artifact = reports.artifact(auth.user_id, id, &name).await?;
+    let disposition = format!("attachment; filename=\"{}\"", artifact.name);
+    Ok((
+        [(header::CONTENT_TYPE, artifact.content_type), (header::CONTENT_DISPOSITION, disposition)],
+        artifact.content,
+    )
+        .into_response())
+}
@@ -1,18 +1,30 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
 use axum::{
-    extract::{Path, State},
-    Json,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, HeaderName, StatusCode},
+    Extension, Json,
 };
-use serde::Serialize;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
 use uuid::Uuid;
 use validator::Validate;
 
 use crate::{
     db::DbPool,
     error::{AppResult, AppError},
-    middleware::AuthUser,
-    models::{Resource, CreateResource, UpdateResource},
+    metering::{MeteringService, UsageEvent},
+    middleware::{AuthUser, Scope},
+    models::{Resource, CreateResource, UpdateResource, ResourceFilter},
+    models::{CreateResourceLink, ResourceGraph, ResourceLink},
+    models::version::{etag, expected_version, settle_update},
+    webhooks::{self, NewEvent},
 };
 
+use super::audit::record_audit;
+
 #[derive(Debug, Serialize)]
 pub struct ResourceResponse {
     pub id: Uuid,
@@ -21,10 +33,49 @@ pub struct ResourceResponse {
     pub data: serde_json::Value,
     pub owner_id: Uuid,
     pub project_id: Uuid,
+    pub version: i64,
     pub created_at: Option<time::OffsetDateTime>,
     pub updated_at: Option<time::OffsetDateTime>,
 }
 
+#[derive(Debug, Default, Deserialize)]
+pub struct DeleteParams {
+    // Delete even if other resources depend on this one
+    #[serde(default)]
+    pub force: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImpactedResource {
+    pub id: Uuid,
+    pub name: String,
+    pub resource_type: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImpactLevelResponse {
+    pub depth: usize,
+    pub resources: Vec<ImpactedResource>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImpactResponse {
+    pub resource_id: Uuid,
+    pub total: usize,
+    pub levels: Vec<ImpactLevelResponse>,
+}
+
+// What webhook consumers see of a resource on either side of a change; `data` stays out
+fn event_summary(resource: &Resource) -> serde_json::Value {
+    json!({
+        "id": resource.id,
+        "name": resource.name,
+        "resource_type": resource.resource_type,
+        "project_id": resource.project_id,
+        "version": resource.version,
+    })
+}
+
 impl From<Resource> for ResourceResponse {
     fn from(resource: Resource) -> Self {
         Self {
@@ -34,6 +85,7 @@ impl From<Resource> for ResourceResponse {
             data: resource.data,
             owner_id: resource.owner_id,
             project_id: resource.project_id,
+            version: resource.version,
             created_at: resource.created_at,
             updated_at: resource.updated_at,
         }
@@ -44,8 +96,10 @@ impl From<Resource> for ResourceResponse {
 pub async fn list_resources_handler(
     State(db): State<DbPool>,
     auth: AuthUser,
+    Query(filter): Query<ResourceFilter>,
 ) -> AppResult<Json<Vec<ResourceResponse>>> {
-    let resources = Resource::find_by_owner(&db, auth.user_id).await
+    auth.require(Scope::ReadResources)?;
+    let resources = Resource::find_filtered(&db, auth.user_id, &filter).await
         .map_err(|e| AppError::Internal(e.to_string()))?;
 
     let response: Vec<ResourceResponse> = resources.into_iter().map(ResourceResponse::from).collect();
@@ -55,15 +109,31 @@ pub async fn list_resources_handler(
 #[axum::debug_handler]
 pub async fn create_resource_handler(
     State(db): State<DbPool>,
+    Extension(metering): Extension<Arc<MeteringService>>,
     auth: AuthUser,
     Json(payload): Json<CreateResource>,
 ) -> AppResult<Json<ResourceResponse>> {
+    auth.require(Scope::WriteResources)?;
+
     // Validate request
     payload.validate().map_err(|e| AppError::Validation(e.to_string()))?;
+    // Budget enforcement can block creation for the resource's project or tags
+    metering.check_creation(auth.user_id, payload.project_id, &payload.data).await?;
 
-    // Create resource
-    let resource = Resource::create(&db, payload, auth.user_id).await
+    // Create resource, with its outbox event in the same transaction
+    let mut tx = db.begin().await.map_err(AppError::Database)?;
+    let resource = Resource::create(&mut *tx, payload, auth.user_id).await
         .map_err(|e| AppError::Internal(e.to_string()))?;
+    let event = NewEvent::new(auth.user_id, "resource.created", "resource", resource.id).with_after(event_summary(&resource));
+    webhooks::enqueue(&mut tx, &event).await?;
+    tx.commit().await.map_err(AppError::Database)?;
+
+    record_audit(&db, &auth, "resource.create", Some(resource.id), json!({
+        "name": resource.name,
+        "resource_type": resource.resource_type,
+        "project_id": resource.project_id,
+    })).await;
+    metering.record(auth.user_id, UsageEvent::ResourceCreated, Utc::now()).await;
 
     Ok(Json(ResourceResponse::from(resource)))
 }
@@ -73,12 +143,13 @@ pub async fn get_resource_handler(
     State(db): State<DbPool>,
     auth: AuthUser,
     Path(id): Path<Uuid>,
-) -> AppResult<Json<ResourceResponse>> {
+) -> AppResult<([(HeaderName, String); 1], Json<ResourceResponse>)> {
+    auth.require(Scope::ReadResources)?;
     let resource = Resource::find_by_id(&db, id, auth.user_id).await
         .map_err(|e| AppError::Internal(e.to_string()))?
         .ok_or_else(|| AppError::NotFound("Resource not found".into()))?;
 
-    Ok(Json(ResourceResponse::from(resource)))
+    Ok(([(header::ETAG, etag(resource.version))], Json(ResourceResponse::from(resource))))
 }
 
 #[axum::debug_handler]
@@ -86,26 +157,48 @@ pub async fn update_resource_handler(
     State(db): State<DbPool>,
     auth: AuthUser,
     Path(id): Path<Uuid>,
+    headers: HeaderMap,
     Json(payload): Json<UpdateResource>,
-) -> AppResult<Json<ResourceResponse>> {
+) -> AppResult<([(HeaderName, String); 1], Json<ResourceResponse>)> {
+    auth.require(Scope::WriteResources)?;
+
     // Validate request
     payload.validate().map_err(|e| AppError::Validation(e.to_string()))?;
+    let if_match = headers.get(header::IF_MATCH).and_then(|value| value.to_str().ok());
+    let expected = expected_version(if_match, payload.version)?;
 
-    // Check if resource exists and user owns it
-    let resource = Resource::find_by_id(&db, id, auth.user_id).await
+    // Check if resource exists and user owns it; read inside the transaction so the event's
+    // before state is the row that was updated
+    let mut tx = db.begin().await.map_err(AppError::Database)?;
+    let mut resource = Resource::find_by_id(&mut *tx, id, auth.user_id).await
         .map_err(|e| AppError::Internal(e.to_string()))?
         .ok_or_else(|| AppError::NotFound("Resource not found".into()))?;
+    let before = event_summary(&resource);
 
-    // Update resource
-    resource.update(&db, payload).await
+    // Update resource, guarded on the version the caller last saw
+    let changed: Vec<&str> = [
+        ("name", payload.name.is_some()),
+        ("resource_type", payload.resource_type.is_some()),
+        ("data", payload.data.is_some()),
+    ]
+    .into_iter()
+    .filter_map(|(field, set)| set.then_some(field))
+    .collect();
+    resource.version = expected;
+    let updated = resource.update(&mut *tx, payload).await
         .map_err(|e| AppError::Internal(e.to_string()))?;
+    let updated_resource = settle_update("Resource", updated, Resource::find_by_id(&db, id, auth.user_id)).await?;
+    let event = NewEvent::new(auth.user_id, "resource.updated", "resource", id)
+        .with_before(before)
+        .with_after(json!({ "fields": changed, "resource": event_summary(&updated_resource) }));
+    webhooks::enqueue(&mut tx, &event).await?;
+    tx.commit().await.map_err(AppError::Database)?;
+    record_audit(&db, &auth, "resource.update", Some(id), json!({
+        "fields": changed,
+        "version": updated_resource.version,
+    })).await;
 
-    // Fetch updated resource
-    let updated_resource = Resource::find_by_id(&db, id, auth.user_id).await
-        .map_err(|e| AppError::Internal(e.to_string()))?
-        .ok_or_else(|| AppError::Internal("Resource disappeared after update".into()))?;
-
-    Ok(Json(ResourceResponse::from(updated_resource)))
+    Ok(([(header::ETAG, etag(updated_resource.version))], Json(ResourceResponse::from(updated_resource))))
 }
 
 #[axum::debug_handler]
@@ -113,13 +206,129 @@ pub async fn delete_resource_handler(
     State(db): State<DbPool>,
     auth: AuthUser,
     Path(id): Path<Uuid>,
+    Query(params): Query<DeleteParams>,
 ) -> AppResult<Json<()>> {
-    let deleted = Resource::delete(&db, id, auth.user_id).await
+    auth.require(Scope::WriteResources)?;
+    let mut tx = db.begin().await.map_err(AppError::Database)?;
+    let resource = Resource::find_by_id(&mut *tx, id, auth.user_id).await
+        .map_err(|e| AppError::Internal(e.to_string()))?
+        .ok_or_else(|| AppError::NotFound("Resource not found".into()))?;
+
+    // Dependents would be left pointing at nothing, so they block the delete unless forced
+    let inbound = ResourceLink::find_inbound(&db, id, auth.user_id).await?;
+    if !inbound.is_empty() && !params.force {
+        return Err(AppError::Conflict {
+            message: format!(
+                "{} resource(s) depend on {}; remove the links or retry with force=true",
+                inbound.len(),
+                id
+            ),
+            current: serde_json::to_value(&inbound).ok(),
+        });
+    }
+
+    let removed_links = ResourceLink::delete_touching(&mut *tx, id, auth.user_id).await?;
+    let deleted = Resource::delete(&mut *tx, id, auth.user_id).await
         .map_err(|e| AppError::Internal(e.to_string()))?;
 
     if !deleted {
         return Err(AppError::NotFound("Resource not found".into()));
     }
+    let event = NewEvent::new(auth.user_id, "resource.deleted", "resource", id).with_before(event_summary(&resource));
+    webhooks::enqueue(&mut tx, &event).await?;
+    tx.commit().await.map_err(AppError::Database)?;
+    let links: Vec<_> = removed_links
+        .iter()
+        .map(|link| json!({
+            "id": link.id,
+            "source_id": link.source_id,
+            "target_id": link.target_id,
+            "link_type": link.link_type,
+        }))
+        .collect();
+    record_audit(&db, &auth, "resource.delete", Some(id), json!({
+        "force": params.force,
+        "removed_links": links,
+    })).await;
 
     Ok(Json(()))
 }
+
+#[axum::debug_handler]
+pub async fn create_resource_link_handler(
+    State(db): State<DbPool>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<CreateResourceLink>,
+) -> AppResult<(StatusCode, Json<ResourceLink>)> {
+    auth.require(Scope::WriteResources)?;
+    payload.validate().map_err(|e| AppError::Validation(e.to_string()))?;
+
+    for resource_id in [id, payload.target_id] {
+        Resource::find_by_id(&db, resource_id, auth.user_id).await?
+            .ok_or_else(|| AppError::NotFound(format!("Resource {} not found", resource_id)))?;
+    }
+    let link = ResourceLink::create(&db, auth.user_id, id, &payload).await?;
+    record_audit(&db, &auth, "resource.link", Some(id), json!({
+        "link_id": link.id,
+        "target_id": link.target_id,
+        "link_type": link.link_type,
+    })).await;
+
+    Ok((StatusCode::CREATED, Json(link)))
+}
+
+#[axum::debug_handler]
+pub async fn delete_resource_link_handler(
+    State(db): State<DbPool>,
+    auth: AuthUser,
+    Path((id, link_id)): Path<(Uuid, Uuid)>,
+) -> AppResult<StatusCode> {
+    auth.require(Scope::WriteResources)?;
+    if !ResourceLink::delete(&db, link_id, id, auth.user_id).await? {
+        return Err(AppError::NotFound("Resource link not found".into()));
+    }
+    record_audit(&db, &auth, "resource.unlink", Some(id), json!({ "link_id": link_id })).await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Everything that depends on the resource, directly or transitively, and so would be affected
+// by changing or deleting it
+#[axum::debug_handler]
+pub async fn resource_impact_handler(
+    State(db): State<DbPool>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<ImpactResponse>> {
+    auth.require(Scope::ReadResources)?;
+    Resource::find_by_id(&db, id, auth.user_id).await?
+        .ok_or_else(|| AppError::NotFound("Resource not found".into()))?;
+
+    let links = ResourceLink::find_by_owner(&db, auth.user_id).await?;
+    let mut resources: HashMap<Uuid, Resource> = Resource::find_by_owner(&db, auth.user_id).await?
+        .into_iter()
+        .map(|resource| (resource.id, resource))
+        .collect();
+
+    let levels: Vec<ImpactLevelResponse> = ResourceGraph::new(&links)
+        .impact(id)
+        .into_iter()
+        .map(|level| ImpactLevelResponse {
+            depth: level.depth,
+            resources: level
+                .resource_ids
+                .iter()
+                .filter_map(|id| resources.remove(id))
+                .map(|resource| ImpactedResource {
+                    id: resource.id,
+                    name: resource.name,
+                    resource_type: resource.resource_type,
+                })
+                .collect(),
+        })
+        .collect();
+    let total = levels.iter().map(|level| level.resources.len()).sum();
+
+    Ok(Json(ImpactResponse { resource_id: id, total, levels }))
+}
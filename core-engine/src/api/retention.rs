@@ -0,0 +1,106 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    db::DbPool,
+    error::AppResult,
+    middleware::{AuthUser, Scope},
+    retention::{
+        DataCategory, EffectivePolicy, PolicyRequest, PurgeExecution, PurgePreview, PurgeProgress, RetentionService,
+        TenantPolicy,
+    },
+};
+
+use super::audit::record_audit;
+
+const DEFAULT_LIMIT: i64 = 50;
+const MAX_LIMIT: i64 = 500;
+
+#[derive(Debug, Deserialize)]
+pub struct ListExecutionsParams {
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RetentionStatusResponse {
+    pub policies: Vec<EffectivePolicy>,
+    pub progress: Vec<PurgeProgress>,
+}
+
+#[axum::debug_handler(state = DbPool)]
+pub async fn get_retention_handler(
+    Extension(retention): Extension<Arc<RetentionService>>,
+    auth: AuthUser,
+    Path(tenant_id): Path<Uuid>,
+) -> AppResult<Json<RetentionStatusResponse>> {
+    auth.require(Scope::ManageTenants)?;
+    Ok(Json(RetentionStatusResponse {
+        policies: retention.policies(tenant_id).await?,
+        progress: retention.progress(tenant_id).await?,
+    }))
+}
+
+#[axum::debug_handler]
+pub async fn set_retention_policy_handler(
+    State(db): State<DbPool>,
+    Extension(retention): Extension<Arc<RetentionService>>,
+    auth: AuthUser,
+    Path((tenant_id, category)): Path<(Uuid, DataCategory)>,
+    Json(payload): Json<PolicyRequest>,
+) -> AppResult<Json<TenantPolicy>> {
+    auth.require(Scope::ManageTenants)?;
+    let policy = retention.set_policy(tenant_id, category, payload, Utc::now()).await?;
+    record_audit(
+        &db,
+        &auth,
+        "tenant.retention",
+        Some(tenant_id),
+        json!({ "category": category, "retain_days": policy.retain_days, "action": policy.action }),
+    )
+    .await;
+    Ok(Json(policy))
+}
+
+#[axum::debug_handler]
+pub async fn reset_retention_policy_handler(
+    State(db): State<DbPool>,
+    Extension(retention): Extension<Arc<RetentionService>>,
+    auth: AuthUser,
+    Path((tenant_id, category)): Path<(Uuid, DataCategory)>,
+) -> AppResult<StatusCode> {
+    auth.require(Scope::ManageTenants)?;
+    retention.reset_policy(tenant_id, category).await?;
+    record_audit(&db, &auth, "tenant.retention_reset", Some(tenant_id), json!({ "category": category })).await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[axum::debug_handler(state = DbPool)]
+pub async fn preview_retention_handler(
+    Extension(retention): Extension<Arc<RetentionService>>,
+    auth: AuthUser,
+    Path(tenant_id): Path<Uuid>,
+) -> AppResult<Json<Vec<PurgePreview>>> {
+    auth.require(Scope::ManageTenants)?;
+    Ok(Json(retention.dry_run(tenant_id, Utc::now()).await?))
+}
+
+#[axum::debug_handler(state = DbPool)]
+pub async fn list_purge_executions_handler(
+    Extension(retention): Extension<Arc<RetentionService>>,
+    auth: AuthUser,
+    Path(tenant_id): Path<Uuid>,
+    Query(params): Query<ListExecutionsParams>,
+) -> AppResult<Json<Vec<PurgeExecution>>> {
+    auth.require(Scope::ManageTenants)?;
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    Ok(Json(retention.executions(tenant_id, limit).await?))
+}
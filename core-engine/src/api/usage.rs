@@ -0,0 +1,102 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    Extension, Json,
+};
+use chrono::{Duration, NaiveDate, Utc};
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    db::DbPool,
+    error::{AppError, AppResult},
+    metering::{MeteringService, QuotaLimits, TenantUsage},
+    middleware::{AuthUser, Scope},
+};
+
+use super::audit::record_audit;
+
+const DEFAULT_RANGE_DAYS: i64 = 30;
+const MAX_RANGE_DAYS: i64 = 366;
+
+#[derive(Debug, Deserialize)]
+pub struct UsageParams {
+    pub since: Option<NaiveDate>,
+    pub until: Option<NaiveDate>,
+}
+
+impl UsageParams {
+    // Inclusive day range, defaulting to the last 30 days
+    pub(super) fn range(&self) -> AppResult<(NaiveDate, NaiveDate)> {
+        let until = self.until.unwrap_or_else(|| Utc::now().date_naive());
+        let since = self.since.unwrap_or(until - Duration::days(DEFAULT_RANGE_DAYS - 1));
+        if since > until {
+            return Err(AppError::InvalidInput("since must not be after until".into()));
+        }
+        if (until - since).num_days() >= MAX_RANGE_DAYS {
+            return Err(AppError::InvalidInput(format!("Usage ranges are limited to {} days", MAX_RANGE_DAYS)));
+        }
+        Ok((since, until))
+    }
+}
+
+// The caller's own daily usage, totals and quota headroom
+#[axum::debug_handler(state = DbPool)]
+pub async fn get_usage_handler(
+    Extension(metering): Extension<Arc<MeteringService>>,
+    auth: AuthUser,
+    Query(params): Query<UsageParams>,
+) -> AppResult<Json<TenantUsage>> {
+    let (since, until) = params.range()?;
+    Ok(Json(metering.usage(auth.user_id, since, until).await?))
+}
+
+#[axum::debug_handler(state = DbPool)]
+pub async fn admin_usage_handler(
+    Extension(metering): Extension<Arc<MeteringService>>,
+    auth: AuthUser,
+    Query(params): Query<UsageParams>,
+) -> AppResult<Json<Vec<TenantUsage>>> {
+    auth.require(Scope::ManageTenants)?;
+    let (since, until) = params.range()?;
+    Ok(Json(metering.rollup(since, until).await?))
+}
+
+#[axum::debug_handler(state = DbPool)]
+pub async fn get_tenant_quotas_handler(
+    Extension(metering): Extension<Arc<MeteringService>>,
+    auth: AuthUser,
+    Path(tenant_id): Path<Uuid>,
+) -> AppResult<Json<QuotaLimits>> {
+    auth.require(Scope::ManageTenants)?;
+    Ok(Json(metering.limits(tenant_id).await?))
+}
+
+#[axum::debug_handler]
+pub async fn set_tenant_quotas_handler(
+    State(db): State<DbPool>,
+    Extension(metering): Extension<Arc<MeteringService>>,
+    auth: AuthUser,
+    Path(tenant_id): Path<Uuid>,
+    Json(limits): Json<QuotaLimits>,
+) -> AppResult<Json<QuotaLimits>> {
+    auth.require(Scope::ManageTenants)?;
+    let negative = [
+        limits.max_projects,
+        limits.max_concurrent_workflow_runs,
+        limits.max_functions,
+        limits.max_monthly_build_minutes,
+    ]
+    .into_iter()
+    .flatten()
+    .any(|limit| limit < 0);
+    if negative {
+        return Err(AppError::Validation("Quota limits must not be negative".into()));
+    }
+
+    metering.set_limits(tenant_id, limits).await?;
+    record_audit(&db, &auth, "tenant.quotas", Some(tenant_id), json!({ "limits": limits })).await;
+    Ok(Json(limits))
+}
@@ -0,0 +1,199 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    db::DbPool,
+    error::{AppError, AppResult},
+    middleware::{AuthUser, Scope},
+    warehouse::{Dataset, Destination, ExportDefinition, ExportRun, NewDestination, NewExport, TableSchema, WarehouseService},
+};
+
+use super::audit::record_audit;
+
+const DEFAULT_LIMIT: i64 = 20;
+const MAX_LIMIT: i64 = 200;
+
+#[derive(Debug, Deserialize)]
+pub struct ListRunsParams {
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct AcceptSchemaRequest {
+    // Ship every row again, e.g. after the destination table was recreated
+    #[serde(default)]
+    pub restart: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DatasetInfo {
+    pub dataset: Dataset,
+    pub schema: TableSchema,
+}
+
+fn service(warehouse: Option<Extension<Arc<WarehouseService>>>) -> AppResult<Arc<WarehouseService>> {
+    warehouse
+        .map(|Extension(service)| service)
+        .ok_or_else(|| AppError::Configuration("Warehouse exports are not configured".into()))
+}
+
+#[axum::debug_handler(state = DbPool)]
+pub async fn list_datasets_handler(
+    warehouse: Option<Extension<Arc<WarehouseService>>>,
+    auth: AuthUser,
+) -> AppResult<Json<Vec<DatasetInfo>>> {
+    auth.require(Scope::ReadResources)?;
+    let service = service(warehouse)?;
+    let datasets = Dataset::ALL.iter().map(|&dataset| DatasetInfo { dataset, schema: service.schema(dataset) }).collect();
+    Ok(Json(datasets))
+}
+
+#[axum::debug_handler]
+pub async fn create_destination_handler(
+    State(db): State<DbPool>,
+    warehouse: Option<Extension<Arc<WarehouseService>>>,
+    auth: AuthUser,
+    Json(destination): Json<NewDestination>,
+) -> AppResult<(StatusCode, Json<Destination>)> {
+    auth.require(Scope::WriteResources)?;
+    auth.require_direct("Configuring warehouse destinations")?;
+    let destination = service(warehouse)?.create_destination(auth.user_id, destination, Utc::now()).await?;
+    record_audit(
+        &db,
+        &auth,
+        "warehouse.destination_create",
+        Some(destination.id),
+        json!({ "name": destination.name, "type": destination.config.kind() }),
+    )
+    .await;
+    Ok((StatusCode::CREATED, Json(destination)))
+}
+
+#[axum::debug_handler(state = DbPool)]
+pub async fn list_destinations_handler(
+    warehouse: Option<Extension<Arc<WarehouseService>>>,
+    auth: AuthUser,
+) -> AppResult<Json<Vec<Destination>>> {
+    auth.require(Scope::ReadResources)?;
+    Ok(Json(service(warehouse)?.destinations(auth.user_id).await?))
+}
+
+#[axum::debug_handler]
+pub async fn delete_destination_handler(
+    State(db): State<DbPool>,
+    warehouse: Option<Extension<Arc<WarehouseService>>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> AppResult<StatusCode> {
+    auth.require(Scope::WriteResources)?;
+    service(warehouse)?.delete_destination(auth.user_id, id).await?;
+    record_audit(&db, &auth, "warehouse.destination_delete", Some(id), json!({})).await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[axum::debug_handler]
+pub async fn create_export_handler(
+    State(db): State<DbPool>,
+    warehouse: Option<Extension<Arc<WarehouseService>>>,
+    auth: AuthUser,
+    Json(export): Json<NewExport>,
+) -> AppResult<(StatusCode, Json<ExportDefinition>)> {
+    auth.require(Scope::WriteResources)?;
+    let export = service(warehouse)?.create_export(auth.user_id, export, Utc::now()).await?;
+    record_audit(
+        &db,
+        &auth,
+        "warehouse.export_create",
+        Some(export.id),
+        json!({ "destination_id": export.destination_id, "dataset": export.dataset, "table": export.table }),
+    )
+    .await;
+    Ok((StatusCode::CREATED, Json(export)))
+}
+
+#[axum::debug_handler(state = DbPool)]
+pub async fn list_exports_handler(
+    warehouse: Option<Extension<Arc<WarehouseService>>>,
+    auth: AuthUser,
+) -> AppResult<Json<Vec<ExportDefinition>>> {
+    auth.require(Scope::ReadResources)?;
+    Ok(Json(service(warehouse)?.exports(auth.user_id).await?))
+}
+
+#[axum::debug_handler(state = DbPool)]
+pub async fn get_export_handler(
+    warehouse: Option<Extension<Arc<WarehouseService>>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<ExportDefinition>> {
+    auth.require(Scope::ReadResources)?;
+    Ok(Json(service(warehouse)?.export(auth.user_id, id).await?))
+}
+
+#[axum::debug_handler]
+pub async fn delete_export_handler(
+    State(db): State<DbPool>,
+    warehouse: Option<Extension<Arc<WarehouseService>>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> AppResult<StatusCode> {
+    auth.require(Scope::WriteResources)?;
+    service(warehouse)?.delete_export(auth.user_id, id).await?;
+    record_audit(&db, &auth, "warehouse.export_delete", Some(id), json!({})).await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Ships whatever changed since the last run straight away
+#[axum::debug_handler(state = DbPool)]
+pub async fn run_export_handler(
+    warehouse: Option<Extension<Arc<WarehouseService>>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> AppResult<(StatusCode, Json<ExportRun>)> {
+    auth.require(Scope::WriteResources)?;
+    Ok((StatusCode::CREATED, Json(service(warehouse)?.run_now(auth.user_id, id, Utc::now()).await?)))
+}
+
+#[axum::debug_handler(state = DbPool)]
+pub async fn list_export_runs_handler(
+    warehouse: Option<Extension<Arc<WarehouseService>>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+    Query(params): Query<ListRunsParams>,
+) -> AppResult<Json<Vec<ExportRun>>> {
+    auth.require(Scope::ReadResources)?;
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    Ok(Json(service(warehouse)?.runs(auth.user_id, id, limit).await?))
+}
+
+#[axum::debug_handler]
+pub async fn accept_export_schema_handler(
+    State(db): State<DbPool>,
+    warehouse: Option<Extension<Arc<WarehouseService>>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+    Json(request): Json<AcceptSchemaRequest>,
+) -> AppResult<Json<ExportDefinition>> {
+    auth.require(Scope::WriteResources)?;
+    let service = service(warehouse)?;
+    let blocked = service.export(auth.user_id, id).await?.progress.blocked_changes;
+    let export = service.accept_schema(auth.user_id, id, request.restart).await?;
+    record_audit(
+        &db,
+        &auth,
+        "warehouse.export_accept_schema",
+        Some(id),
+        json!({ "changes": blocked, "restart": request.restart }),
+    )
+    .await;
+    Ok(Json(export))
+}
@@ -0,0 +1,164 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    db::DbPool,
+    error::AppResult,
+    middleware::{AuthUser, Scope},
+    webhooks::{DeliveryAttempt, EndpointRequest, EndpointUpdate, WebhookDelivery, WebhookEndpoint, WebhookService},
+};
+
+use super::audit::record_audit;
+
+const DEFAULT_LIMIT: i64 = 50;
+const MAX_LIMIT: i64 = 500;
+
+#[derive(Debug, Deserialize)]
+pub struct ListDeliveriesParams {
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RedeliverRequest {
+    pub since: DateTime<Utc>,
+    pub until: DateTime<Utc>,
+}
+
+// The signing secret is only ever shown here
+#[derive(Debug, Serialize)]
+pub struct CreatedEndpointResponse {
+    #[serde(flatten)]
+    pub endpoint: WebhookEndpoint,
+    pub secret: String,
+}
+
+#[axum::debug_handler]
+pub async fn create_webhook_handler(
+    State(db): State<DbPool>,
+    Extension(webhooks): Extension<Arc<WebhookService>>,
+    auth: AuthUser,
+    Json(payload): Json<EndpointRequest>,
+) -> AppResult<(StatusCode, Json<CreatedEndpointResponse>)> {
+    auth.require(Scope::ManageWebhooks)?;
+    let (endpoint, secret) = webhooks.create_endpoint(auth.user_id, payload, Utc::now()).await?;
+    record_audit(&db, &auth, "webhook.create", Some(endpoint.id), json!({ "url": endpoint.url, "event_types": endpoint.event_types })).await;
+    Ok((StatusCode::CREATED, Json(CreatedEndpointResponse { endpoint, secret })))
+}
+
+#[axum::debug_handler(state = DbPool)]
+pub async fn list_webhooks_handler(
+    Extension(webhooks): Extension<Arc<WebhookService>>,
+    auth: AuthUser,
+) -> AppResult<Json<Vec<WebhookEndpoint>>> {
+    auth.require(Scope::ManageWebhooks)?;
+    Ok(Json(webhooks.endpoints(auth.user_id).await?))
+}
+
+#[axum::debug_handler(state = DbPool)]
+pub async fn get_webhook_handler(
+    Extension(webhooks): Extension<Arc<WebhookService>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<WebhookEndpoint>> {
+    auth.require(Scope::ManageWebhooks)?;
+    Ok(Json(webhooks.endpoint(auth.user_id, id).await?))
+}
+
+#[axum::debug_handler]
+pub async fn update_webhook_handler(
+    State(db): State<DbPool>,
+    Extension(webhooks): Extension<Arc<WebhookService>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<EndpointUpdate>,
+) -> AppResult<Json<WebhookEndpoint>> {
+    auth.require(Scope::ManageWebhooks)?;
+    let endpoint = webhooks.update_endpoint(auth.user_id, id, payload, Utc::now()).await?;
+    record_audit(
+        &db,
+        &auth,
+        "webhook.update",
+        Some(id),
+        json!({ "url": endpoint.url, "event_types": endpoint.event_types, "active": endpoint.active }),
+    )
+    .await;
+    Ok(Json(endpoint))
+}
+
+#[axum::debug_handler]
+pub async fn delete_webhook_handler(
+    State(db): State<DbPool>,
+    Extension(webhooks): Extension<Arc<WebhookService>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> AppResult<StatusCode> {
+    auth.require(Scope::ManageWebhooks)?;
+    webhooks.delete_endpoint(auth.user_id, id).await?;
+    record_audit(&db, &auth, "webhook.delete", Some(id), json!({})).await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[axum::debug_handler(state = DbPool)]
+pub async fn test_webhook_handler(
+    Extension(webhooks): Extension<Arc<WebhookService>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<DeliveryAttempt>> {
+    auth.require(Scope::ManageWebhooks)?;
+    Ok(Json(webhooks.send_test(auth.user_id, id, Utc::now()).await?))
+}
+
+#[axum::debug_handler]
+pub async fn resume_webhook_handler(
+    State(db): State<DbPool>,
+    Extension(webhooks): Extension<Arc<WebhookService>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<WebhookEndpoint>> {
+    auth.require(Scope::ManageWebhooks)?;
+    let endpoint = webhooks.resume(auth.user_id, id).await?;
+    record_audit(&db, &auth, "webhook.resume", Some(id), json!({})).await;
+    Ok(Json(endpoint))
+}
+
+#[axum::debug_handler(state = DbPool)]
+pub async fn list_webhook_deliveries_handler(
+    Extension(webhooks): Extension<Arc<WebhookService>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+    Query(params): Query<ListDeliveriesParams>,
+) -> AppResult<Json<Vec<WebhookDelivery>>> {
+    auth.require(Scope::ManageWebhooks)?;
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    Ok(Json(webhooks.deliveries(auth.user_id, id, limit).await?))
+}
+
+#[axum::debug_handler]
+pub async fn redeliver_webhook_handler(
+    State(db): State<DbPool>,
+    Extension(webhooks): Extension<Arc<WebhookService>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<RedeliverRequest>,
+) -> AppResult<(StatusCode, Json<serde_json::Value>)> {
+    auth.require(Scope::ManageWebhooks)?;
+    let queued = webhooks.redeliver(auth.user_id, id, payload.since, payload.until, Utc::now()).await?;
+    record_audit(
+        &db,
+        &auth,
+        "webhook.redeliver",
+        Some(id),
+        json!({ "since": payload.since, "until": payload.until, "queued": queued }),
+    )
+    .await;
+    Ok((StatusCode::ACCEPTED, Json(json!({ "queued": queued }))))
+}
@@ -0,0 +1,98 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use sqlx::PgPool; // CockroachDB uses PostgreSQL protocol
+use uuid::Uuid;
+
+use crate::{
+    error::AppResult,
+    middleware::{AuthUser, Scope},
+    orgs::{OrgPermission, OrgService},
+    workflow_runs::{FailureReport, NewWorkflowRun, RunPage, RunQuery, WorkflowRunRecord, WorkflowRunService, DEFAULT_PAGE_SIZE},
+};
+
+use super::projects::authorized_project;
+
+#[derive(Debug, Deserialize)]
+pub struct RunSearchRequest {
+    #[serde(flatten)]
+    pub query: RunQuery,
+    // `next_cursor` from the previous page
+    pub after: Option<Uuid>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FailureParams {
+    // Defaults to the seven days up to `until`
+    pub since: Option<DateTime<Utc>>,
+    // Defaults to now
+    pub until: Option<DateTime<Utc>>,
+    pub workflow_id: Option<String>,
+}
+
+// Executors report each run here as it changes state; reporting a run again replaces it
+#[axum::debug_handler]
+pub async fn record_workflow_run_handler(
+    State(pool): State<PgPool>,
+    Extension(orgs): Extension<Arc<OrgService>>,
+    Extension(runs): Extension<Arc<WorkflowRunService>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+    Json(run): Json<NewWorkflowRun>,
+) -> AppResult<Json<WorkflowRunRecord>> {
+    auth.require(Scope::WriteProjects)?;
+    let project = authorized_project(&pool, &orgs, &auth, id, OrgPermission::WriteProjects).await?;
+    Ok(Json(runs.record(project.id, run).await?))
+}
+
+#[axum::debug_handler]
+pub async fn search_workflow_runs_handler(
+    State(pool): State<PgPool>,
+    Extension(orgs): Extension<Arc<OrgService>>,
+    Extension(runs): Extension<Arc<WorkflowRunService>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+    Json(request): Json<RunSearchRequest>,
+) -> AppResult<Json<RunPage>> {
+    auth.require(Scope::ReadProjects)?;
+    let project = authorized_project(&pool, &orgs, &auth, id, OrgPermission::ReadProjects).await?;
+    let limit = request.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    Ok(Json(runs.search(project.id, &request.query, request.after, limit).await?))
+}
+
+#[axum::debug_handler]
+pub async fn get_workflow_run_handler(
+    State(pool): State<PgPool>,
+    Extension(orgs): Extension<Arc<OrgService>>,
+    Extension(runs): Extension<Arc<WorkflowRunService>>,
+    auth: AuthUser,
+    Path((id, run_id)): Path<(Uuid, Uuid)>,
+) -> AppResult<Json<WorkflowRunRecord>> {
+    auth.require(Scope::ReadProjects)?;
+    let project = authorized_project(&pool, &orgs, &auth, id, OrgPermission::ReadProjects).await?;
+    Ok(Json(runs.run(project.id, run_id).await?))
+}
+
+// Failed task runs grouped by task and error code, most frequent first
+#[axum::debug_handler]
+pub async fn workflow_failures_handler(
+    State(pool): State<PgPool>,
+    Extension(orgs): Extension<Arc<OrgService>>,
+    Extension(runs): Extension<Arc<WorkflowRunService>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+    Query(params): Query<FailureParams>,
+) -> AppResult<Json<FailureReport>> {
+    auth.require(Scope::ReadProjects)?;
+    let project = authorized_project(&pool, &orgs, &auth, id, OrgPermission::ReadProjects).await?;
+    let report = runs
+        .failures(project.id, params.since, params.until, params.workflow_id.as_deref(), Utc::now())
+        .await?;
+    Ok(Json(report))
+}
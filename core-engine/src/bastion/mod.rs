@@ -0,0 +1,854 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::async_trait;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::{DateTime, Duration, Utc};
+use openssl::pkey::PKey;
+use serde::{Deserialize, Serialize};
+use sirsi_compute_manager::fleet::{CommandBackend, Instance, InstanceState};
+use sirsi_key_vault::ssh::{CertificateRequest, SshCertificate, SshCertificateAuthority, MAX_CERTIFICATE_TTL_MINUTES};
+use sirsi_key_vault::{KeyVaultError, SecretString};
+use sirsi_object_store::ObjectStore;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::error::{AppError, AppResult};
+
+pub mod recording;
+pub mod store;
+
+pub use recording::{Recorder, RecordedChunk, RecordingSummary, Replay, StreamDirection};
+pub use store::{InMemorySshSessionStore, PgSshSessionStore};
+
+pub const DEFAULT_TTL_MINUTES: i64 = 5;
+pub const SSH_PORT: u16 = 22;
+pub const TRUSTED_CA_PATH: &str = "/etc/ssh/sirsi_user_ca.pub";
+pub const RECORDING_DISCLOSURE: &str =
+    "This session is recorded. Keystrokes and terminal output are stored and may be reviewed by administrators.";
+// The proxy's own certificate only has to last until the SSH handshake is done
+const PROXY_CERT_MINUTES: i64 = 1;
+const PROXY_BUFFER: usize = 8 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "VARCHAR", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum SessionMode {
+    // The operator connects straight to the instance with a certificate for their own key
+    Direct,
+    // The operator attaches through the broker, which holds the certificate and records the stream
+    Recorded,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "VARCHAR", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum SessionStatus {
+    Active,
+    Closed,
+    Terminated,
+    // Never stored: an active session whose certificate lapsed, or that was never attached in time
+    Expired,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SshSession {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub user_id: Uuid,
+    pub fleet_id: String,
+    pub group_id: String,
+    pub instance_id: String,
+    pub host: String,
+    pub login: String,
+    pub mode: SessionMode,
+    pub status: SessionStatus,
+    pub command: Option<String>,
+    // Serial of the certificate the session was opened with; logged by sshd on the instance
+    pub certificate_serial: Option<String>,
+    // Set on every recorded session, and written to the operator's terminal before anything else
+    pub disclosure: Option<String>,
+    pub created_at: DateTime<Utc>,
+    // When the certificate lapses, or for recorded sessions the deadline to attach
+    pub expires_at: DateTime<Utc>,
+    pub attached_at: Option<DateTime<Utc>>,
+    pub ended_at: Option<DateTime<Utc>>,
+    pub terminated_by: Option<Uuid>,
+    pub recording: Option<RecordingSummary>,
+}
+
+impl SshSession {
+    pub(crate) fn effective(mut self, now: DateTime<Utc>) -> Self {
+        if self.status == SessionStatus::Active && self.attached_at.is_none() && self.expires_at <= now {
+            self.status = SessionStatus::Expired;
+        }
+        self
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct StartSession {
+    pub login: String,
+    // The operator's OpenSSH public key; required for direct sessions
+    #[serde(default)]
+    pub public_key: Option<String>,
+    #[serde(default)]
+    pub record: bool,
+    // Restricts the session to this command
+    #[serde(default)]
+    pub command: Option<String>,
+    #[serde(default)]
+    pub ttl_minutes: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ConnectionDetails {
+    Direct {
+        host: String,
+        port: u16,
+        login: String,
+        // Save next to the private key as `<key>-cert.pub`
+        certificate: String,
+        valid_before: DateTime<Utc>,
+    },
+    Recorded {
+        // WebSocket carrying the terminal stream
+        attach_path: String,
+        attach_before: DateTime<Utc>,
+        disclosure: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionGrant {
+    pub session: SshSession,
+    pub connection: ConnectionDetails,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SessionFilter {
+    #[serde(default)]
+    pub tenant_id: Option<Uuid>,
+    #[serde(default)]
+    pub status: Option<SessionStatus>,
+}
+
+#[async_trait]
+pub trait SshSessionStore: Send + Sync {
+    async fn insert(&self, session: &SshSession) -> AppResult<()>;
+    async fn get(&self, id: Uuid) -> AppResult<Option<SshSession>>;
+    // Newest first, with `Active` and `Expired` judged at `now`
+    async fn list(&self, filter: &SessionFilter, now: DateTime<Utc>, limit: i64) -> AppResult<Vec<SshSession>>;
+    // Only the first attach of an active session succeeds
+    async fn mark_attached(&self, id: Uuid, certificate_serial: &str, at: DateTime<Utc>) -> AppResult<bool>;
+    async fn save_recording(&self, id: Uuid, recording: &RecordingSummary) -> AppResult<()>;
+    // Ends an active session; false when it had already ended
+    async fn end(&self, id: Uuid, status: SessionStatus, at: DateTime<Utc>, by: Option<Uuid>) -> AppResult<bool>;
+}
+
+#[async_trait]
+pub trait InstanceDirectory: Send + Sync {
+    async fn instance(&self, fleet_id: &str, group_id: &str, instance_id: &str) -> AppResult<Option<Instance>>;
+}
+
+// Finds instances through the same provider backend fleet commands use
+pub struct FleetInstanceDirectory {
+    backend: Arc<dyn CommandBackend>,
+}
+
+impl FleetInstanceDirectory {
+    pub fn new(backend: Arc<dyn CommandBackend>) -> Self {
+        Self { backend }
+    }
+}
+
+#[async_trait]
+impl InstanceDirectory for FleetInstanceDirectory {
+    async fn instance(&self, fleet_id: &str, group_id: &str, instance_id: &str) -> AppResult<Option<Instance>> {
+        let instances = self
+            .backend
+            .list_instances(fleet_id, group_id)
+            .await
+            .map_err(AppError::from_provider)?;
+        Ok(instances.into_iter().find(|i| i.instance_id == instance_id))
+    }
+}
+
+pub trait SessionStream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> SessionStream for T {}
+
+#[derive(Debug, Clone)]
+pub struct SessionTarget {
+    pub host: String,
+    pub port: u16,
+    pub login: String,
+    pub command: Option<String>,
+}
+
+pub struct SessionCredential {
+    // PKCS#8 PEM of a key generated for this one connection
+    pub private_key_pem: SecretString,
+    pub certificate: SshCertificate,
+}
+
+// The SSH client side of a recorded session: authenticates to the instance with the credential
+// and returns the session's terminal stream
+#[async_trait]
+pub trait SessionTransport: Send + Sync {
+    async fn connect(&self, target: &SessionTarget, credential: SessionCredential) -> AppResult<Box<dyn SessionStream>>;
+}
+
+fn vault_error(e: KeyVaultError) -> AppError {
+    match e {
+        KeyVaultError::Validation(msg) => AppError::Validation(msg),
+        KeyVaultError::Permission(msg) => AppError::Forbidden(msg),
+        KeyVaultError::NotFound(msg) => AppError::NotFound(msg),
+        e => AppError::Internal(format!("SSH certificate operation failed: {}", e)),
+    }
+}
+
+fn io_error(e: std::io::Error) -> AppError {
+    AppError::Connection(format!("SSH session stream failed: {}", e))
+}
+
+fn valid_login(login: &str) -> bool {
+    !login.is_empty()
+        && login.len() <= 32
+        && !login.starts_with('-')
+        && login.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+// A one-off Ed25519 key for the proxy's side of a recorded session
+pub(crate) fn ephemeral_key() -> AppResult<(String, SecretString)> {
+    let key = PKey::generate_ed25519().map_err(|e| AppError::Internal(format!("Failed to generate session key: {}", e)))?;
+    let raw = key
+        .raw_public_key()
+        .map_err(|e| AppError::Internal(format!("Failed to encode session key: {}", e)))?;
+    let pem = key
+        .private_key_to_pem_pkcs8()
+        .map_err(|e| AppError::Internal(format!("Failed to encode session key: {}", e)))?;
+    let mut blob = Vec::with_capacity(51);
+    for field in [b"ssh-ed25519".as_slice(), raw.as_slice()] {
+        blob.extend_from_slice(&(field.len() as u32).to_be_bytes());
+        blob.extend_from_slice(field);
+    }
+    let pem = String::from_utf8(pem).map_err(|_| AppError::Internal("Session key PEM is not UTF-8".into()))?;
+    Ok((format!("ssh-ed25519 {}", BASE64.encode(blob)), SecretString::new(pem)))
+}
+
+// Brokers shell access to fleet instances. Access is granted per session with a certificate that
+// lapses within minutes; instances only need to trust the CA (see `trust_script`).
+//
+// Recorded sessions are refused with a configuration error until recording storage and a
+// transport are attached. Terminating a direct session can't cut a connection that's already
+// open, since the broker isn't in the path; it stops the session counting as active and its
+// certificate lapses on schedule. Recorded sessions are cut off immediately.
+pub struct SessionBroker {
+    store: Arc<dyn SshSessionStore>,
+    ca: Arc<SshCertificateAuthority>,
+    instances: Arc<dyn InstanceDirectory>,
+    recordings: Option<Arc<dyn ObjectStore>>,
+    transport: Option<Arc<dyn SessionTransport>>,
+    // Empty allows any login but root
+    allowed_logins: Vec<String>,
+    require_recording: bool,
+    // Source addresses the proxy connects from; the proxy's certificates are pinned to them
+    proxy_addresses: Vec<String>,
+    live: Mutex<HashMap<Uuid, CancellationToken>>,
+}
+
+impl SessionBroker {
+    pub fn new(store: Arc<dyn SshSessionStore>, ca: Arc<SshCertificateAuthority>, instances: Arc<dyn InstanceDirectory>) -> Self {
+        Self {
+            store,
+            ca,
+            instances,
+            recordings: None,
+            transport: None,
+            allowed_logins: Vec::new(),
+            require_recording: false,
+            proxy_addresses: Vec::new(),
+            live: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn with_recording(mut self, recordings: Arc<dyn ObjectStore>, transport: Arc<dyn SessionTransport>) -> Self {
+        self.recordings = Some(recordings);
+        self.transport = Some(transport);
+        self
+    }
+
+    pub fn with_allowed_logins(mut self, logins: Vec<String>) -> Self {
+        self.allowed_logins = logins;
+        self
+    }
+
+    // Refuses direct sessions, so every session goes through the recording proxy
+    pub fn with_required_recording(mut self) -> Self {
+        self.require_recording = true;
+        self
+    }
+
+    pub fn with_proxy_addresses(mut self, addresses: Vec<String>) -> Self {
+        self.proxy_addresses = addresses;
+        self
+    }
+
+    pub async fn trusted_ca_keys(&self) -> AppResult<Vec<String>> {
+        self.ca.trusted_keys().await.map_err(vault_error)
+    }
+
+    // Startup script snippet that makes sshd trust the CA; add it to instance groups' startup
+    // scripts or push it with the fleet command runner after a CA rotation
+    pub async fn trust_script(&self) -> AppResult<String> {
+        let keys = self.trusted_ca_keys().await?.join("\n");
+        Ok(format!(
+            "install -m 0644 /dev/null {path}\n\
+            cat > {path} <<'SIRSI_CA'\n{keys}\nSIRSI_CA\n\
+            grep -q '^TrustedUserCAKeys {path}' /etc/ssh/sshd_config || echo 'TrustedUserCAKeys {path}' >> /etc/ssh/sshd_config\n\
+            systemctl reload sshd 2>/dev/null || systemctl reload ssh\n",
+            path = TRUSTED_CA_PATH,
+            keys = keys,
+        ))
+    }
+
+    fn check_login(&self, login: &str) -> AppResult<()> {
+        if !valid_login(login) {
+            return Err(AppError::Validation(format!("'{}' is not a valid login", login)));
+        }
+        let allowed = if self.allowed_logins.is_empty() {
+            login != "root"
+        } else {
+            self.allowed_logins.iter().any(|l| l == login)
+        };
+        if !allowed {
+            return Err(AppError::Forbidden(format!("Sessions as {} are not allowed", login)));
+        }
+        Ok(())
+    }
+
+    fn key_id(tenant_id: Uuid, user_id: Uuid, session_id: Uuid) -> String {
+        format!("sirsi:{}:{}:{}", tenant_id, user_id, session_id)
+    }
+
+    // The caller's scopes are checked by the route; this checks the login, the instance and the
+    // recording policy, then issues the certificate or prepares the recorded session
+    #[allow(clippy::too_many_arguments)]
+    pub async fn start_session(
+        &self,
+        tenant_id: Uuid,
+        user_id: Uuid,
+        fleet_id: &str,
+        group_id: &str,
+        instance_id: &str,
+        request: StartSession,
+        now: DateTime<Utc>,
+    ) -> AppResult<SessionGrant> {
+        self.check_login(&request.login)?;
+        let ttl = Duration::minutes(request.ttl_minutes.unwrap_or(DEFAULT_TTL_MINUTES));
+        if ttl <= Duration::zero() || ttl > Duration::minutes(MAX_CERTIFICATE_TTL_MINUTES) {
+            return Err(AppError::Validation(format!(
+                "ttl_minutes must be between 1 and {}",
+                MAX_CERTIFICATE_TTL_MINUTES
+            )));
+        }
+        if self.require_recording && !request.record {
+            return Err(AppError::Forbidden("Sessions to fleet instances must be recorded".into()));
+        }
+        if request.record && (self.recordings.is_none() || self.transport.is_none()) {
+            return Err(AppError::Configuration("Recorded sessions are not configured".into()));
+        }
+        let instance = self
+            .instances
+            .instance(fleet_id, group_id, instance_id)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("Instance {} not found in {}/{}", instance_id, fleet_id, group_id)))?;
+        if !matches!(instance.state, InstanceState::Running) {
+            return Err(AppError::Validation(format!("Instance {} is not running", instance_id)));
+        }
+
+        let id = Uuid::new_v4();
+        let mut session = SshSession {
+            id,
+            tenant_id,
+            user_id,
+            fleet_id: fleet_id.to_string(),
+            group_id: group_id.to_string(),
+            instance_id: instance_id.to_string(),
+            // The proxy reaches instances on their private address; operators connecting directly
+            // use the public one where there is one
+            host: match (request.record, &instance.public_ip) {
+                (false, Some(public_ip)) => public_ip.clone(),
+                _ => instance.private_ip.clone(),
+            },
+            login: request.login.clone(),
+            mode: if request.record { SessionMode::Recorded } else { SessionMode::Direct },
+            status: SessionStatus::Active,
+            command: request.command.clone(),
+            certificate_serial: None,
+            disclosure: request.record.then(|| RECORDING_DISCLOSURE.to_string()),
+            created_at: now,
+            expires_at: now + ttl,
+            attached_at: None,
+            ended_at: None,
+            terminated_by: None,
+            recording: None,
+        };
+
+        let connection = if request.record {
+            ConnectionDetails::Recorded {
+                attach_path: format!("/ssh-sessions/{}/attach", id),
+                attach_before: session.expires_at,
+                disclosure: RECORDING_DISCLOSURE.to_string(),
+            }
+        } else {
+            let public_key = request
+                .public_key
+                .as_deref()
+                .ok_or_else(|| AppError::Validation("Direct sessions need the operator's public_key".into()))?;
+            let mut cert_request =
+                CertificateRequest::new(public_key, Self::key_id(tenant_id, user_id, id), vec![request.login.clone()], ttl);
+            if let Some(command) = &request.command {
+                cert_request = cert_request.with_force_command(command.clone());
+            }
+            let certificate = self.ca.issue(&cert_request).await.map_err(vault_error)?;
+            session.certificate_serial = Some(certificate.serial.to_string());
+            session.expires_at = certificate.valid_before;
+            ConnectionDetails::Direct {
+                host: session.host.clone(),
+                port: SSH_PORT,
+                login: request.login,
+                certificate: certificate.encoded,
+                valid_before: certificate.valid_before,
+            }
+        };
+
+        self.store.insert(&session).await?;
+        info!(
+            "Started {:?} SSH session {} for {} as {} on {}",
+            session.mode, id, user_id, session.login, instance_id
+        );
+        Ok(SessionGrant { session, connection })
+    }
+
+    pub async fn session(&self, id: Uuid, now: DateTime<Utc>) -> AppResult<SshSession> {
+        self.store
+            .get(id)
+            .await?
+            .map(|s| s.effective(now))
+            .ok_or_else(|| AppError::NotFound(format!("SSH session {} not found", id)))
+    }
+
+    pub async fn list(&self, filter: &SessionFilter, now: DateTime<Utc>, limit: i64) -> AppResult<Vec<SshSession>> {
+        self.store.list(filter, now, limit.clamp(1, 500)).await
+    }
+
+    // Proxies a recorded session between `client` and the instance until either side closes or
+    // the session is terminated, recording both directions
+    pub async fn attach<S: AsyncRead + AsyncWrite + Unpin + Send>(
+        &self,
+        tenant_id: Uuid,
+        user_id: Uuid,
+        id: Uuid,
+        mut client: S,
+    ) -> AppResult<SshSession> {
+        let (Some(recordings), Some(transport)) = (&self.recordings, &self.transport) else {
+            return Err(AppError::Configuration("Recorded sessions are not configured".into()));
+        };
+        let now = Utc::now();
+        let session = self.session(id, now).await?;
+        if session.tenant_id != tenant_id || session.user_id != user_id {
+            return Err(AppError::NotFound(format!("SSH session {} not found", id)));
+        }
+        if session.mode != SessionMode::Recorded || session.status != SessionStatus::Active || session.attached_at.is_some() {
+            return Err(AppError::Validation(format!("SSH session {} can't be attached", id)));
+        }
+
+        let (public_key, private_key_pem) = ephemeral_key()?;
+        let mut cert_request = CertificateRequest::new(
+            public_key,
+            Self::key_id(tenant_id, user_id, id),
+            vec![session.login.clone()],
+            Duration::minutes(PROXY_CERT_MINUTES),
+        )
+        .with_source_addresses(self.proxy_addresses.clone());
+        if let Some(command) = &session.command {
+            cert_request = cert_request.with_force_command(command.clone());
+        }
+        let certificate = self.ca.issue(&cert_request).await.map_err(vault_error)?;
+        if !self.store.mark_attached(id, &certificate.serial.to_string(), now).await? {
+            return Err(AppError::Validation(format!("SSH session {} can't be attached", id)));
+        }
+
+        let target = SessionTarget {
+            host: session.host.clone(),
+            port: SSH_PORT,
+            login: session.login.clone(),
+            command: session.command.clone(),
+        };
+        let cancel = CancellationToken::new();
+        self.live.lock().await.insert(id, cancel.clone());
+        let mut recorder = Recorder::new(recordings.clone(), id);
+        let result = match transport.connect(&target, SessionCredential { private_key_pem, certificate }).await {
+            Ok(upstream) => self.pump(id, &mut client, upstream, &mut recorder, &cancel).await,
+            Err(e) => Err(e),
+        };
+        self.live.lock().await.remove(&id);
+
+        // Whatever happened, keep what was captured
+        let flushed = recorder.flush().await;
+        self.store.save_recording(id, recorder.summary()).await?;
+        if !cancel.is_cancelled() {
+            self.store.end(id, SessionStatus::Closed, Utc::now(), None).await?;
+        }
+        flushed?;
+        if let Err(e) = result {
+            warn!("SSH session {} ended with an error: {}", id, e);
+            return Err(e);
+        }
+        self.session(id, Utc::now()).await
+    }
+
+    async fn pump<S: AsyncRead + AsyncWrite + Unpin + Send>(
+        &self,
+        id: Uuid,
+        client: &mut S,
+        upstream: Box<dyn SessionStream>,
+        recorder: &mut Recorder,
+        cancel: &CancellationToken,
+    ) -> AppResult<()> {
+        let banner = format!("*** {} ***\r\n", RECORDING_DISCLOSURE);
+        self.relay(id, recorder, StreamDirection::Output, banner.as_bytes(), client).await?;
+
+        let (mut upstream_read, mut upstream_write) = tokio::io::split(upstream);
+        let mut from_client = vec![0u8; PROXY_BUFFER];
+        let mut from_instance = vec![0u8; PROXY_BUFFER];
+        loop {
+            tokio::select! {
+                _ = cancel.cancelled() => {
+                    let notice = b"\r\n*** Session terminated by an administrator ***\r\n";
+                    return self.relay(id, recorder, StreamDirection::Output, notice, client).await;
+                }
+                read = client.read(&mut from_client) => {
+                    let n = read.map_err(io_error)?;
+                    if n == 0 {
+                        return Ok(());
+                    }
+                    self.relay(id, recorder, StreamDirection::Input, &from_client[..n], &mut upstream_write).await?;
+                }
+                read = upstream_read.read(&mut from_instance) => {
+                    let n = read.map_err(io_error)?;
+                    if n == 0 {
+                        return Ok(());
+                    }
+                    self.relay(id, recorder, StreamDirection::Output, &from_instance[..n], client).await?;
+                }
+            }
+        }
+    }
+
+    // Records before forwarding, so nothing reaches either side without being captured
+    async fn relay<W: AsyncWrite + Unpin>(
+        &self,
+        id: Uuid,
+        recorder: &mut Recorder,
+        direction: StreamDirection,
+        data: &[u8],
+        to: &mut W,
+    ) -> AppResult<()> {
+        if recorder.record(direction, data).await? {
+            self.store.save_recording(id, recorder.summary()).await?;
+        }
+        to.write_all(data).await.map_err(io_error)?;
+        to.flush().await.map_err(io_error)
+    }
+
+    pub async fn terminate(&self, id: Uuid, by: Uuid, now: DateTime<Utc>) -> AppResult<SshSession> {
+        if !self.store.end(id, SessionStatus::Terminated, now, Some(by)).await? {
+            let session = self.session(id, now).await?;
+            return Err(AppError::Validation(format!("SSH session {} has already ended ({:?})", id, session.status)));
+        }
+        if let Some(cancel) = self.live.lock().await.get(&id) {
+            cancel.cancel();
+        }
+        info!("SSH session {} terminated by {}", id, by);
+        self.session(id, now).await
+    }
+
+    // The session's recording, verified against the hash chain and the head saved with the session
+    pub async fn replay(&self, id: Uuid) -> AppResult<Replay> {
+        let recordings = self
+            .recordings
+            .as_ref()
+            .ok_or_else(|| AppError::Configuration("Recorded sessions are not configured".into()))?;
+        let session = self.session(id, Utc::now()).await?;
+        let summary = session
+            .recording
+            .ok_or_else(|| AppError::NotFound(format!("SSH session {} has no recording", id)))?;
+        recording::replay(recordings.as_ref(), id, &summary).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sirsi_key_vault::secret::{
+        AccessPolicy, AccessPolicyManager, InMemoryAccessPolicyManager, InMemoryAuditLogger, SecretAction, SecretPermission,
+    };
+    use sirsi_key_vault::signing::SigningService;
+    use sirsi_object_store::{LocalFileStore, PutOptions};
+    use futures::StreamExt;
+
+    struct Instances(Vec<Instance>);
+
+    #[async_trait]
+    impl InstanceDirectory for Instances {
+        async fn instance(&self, fleet_id: &str, group_id: &str, instance_id: &str) -> AppResult<Option<Instance>> {
+            Ok(self
+                .0
+                .iter()
+                .find(|i| i.fleet_id == fleet_id && i.group_id == group_id && i.instance_id == instance_id)
+                .cloned())
+        }
+    }
+
+    fn instance(id: &str, state: InstanceState) -> Instance {
+        Instance {
+            instance_id: id.into(),
+            group_id: "blue".into(),
+            fleet_id: "web".into(),
+            instance_type: "t3.micro".into(),
+            private_ip: "10.0.0.7".into(),
+            public_ip: Some("203.0.113.7".into()),
+            state,
+            launch_time: Utc::now(),
+            labels: Default::default(),
+            metrics: None,
+        }
+    }
+
+    // A shell that echoes each line back in upper case, then prompts again
+    #[derive(Default)]
+    struct EchoShell {
+        connections: std::sync::Mutex<Vec<(SessionTarget, SshCertificate)>>,
+    }
+
+    #[async_trait]
+    impl SessionTransport for EchoShell {
+        async fn connect(&self, target: &SessionTarget, credential: SessionCredential) -> AppResult<Box<dyn SessionStream>> {
+            self.connections.lock().unwrap().push((target.clone(), credential.certificate));
+            let (ours, mut shell) = tokio::io::duplex(1024);
+            tokio::spawn(async move {
+                let _ = shell.write_all(b"$ ").await;
+                let mut buf = [0u8; 256];
+                while let Ok(n) = shell.read(&mut buf).await {
+                    if n == 0 || shell.write_all(&buf[..n].to_ascii_uppercase()).await.is_err() {
+                        break;
+                    }
+                    let _ = shell.write_all(b"$ ").await;
+                }
+            });
+            Ok(Box::new(ours))
+        }
+    }
+
+    async fn authority() -> Arc<SshCertificateAuthority> {
+        let policies = InMemoryAccessPolicyManager::new();
+        policies
+            .create_policy(AccessPolicy {
+                id: "bastion".into(),
+                name: "bastion".into(),
+                description: None,
+                principals: vec!["svc-bastion".into()],
+                permissions: vec![SecretPermission {
+                    actions: vec![SecretAction::Read, SecretAction::Write, SecretAction::Sign],
+                    secret_patterns: vec!["signing/*".into()],
+                }],
+                conditions: None,
+            })
+            .await
+            .unwrap();
+        let signing = Arc::new(SigningService::new(Arc::new(policies), Arc::new(InMemoryAuditLogger::new())));
+        let ca = Arc::new(SshCertificateAuthority::new(signing, "svc-bastion"));
+        ca.ensure_key().await.unwrap();
+        ca
+    }
+
+    async fn new_broker() -> SessionBroker {
+        SessionBroker::new(
+            Arc::new(InMemorySshSessionStore::new()),
+            authority().await,
+            Arc::new(Instances(vec![instance("i-1", InstanceState::Running), instance("i-2", InstanceState::Stopped)])),
+        )
+    }
+
+    fn start(login: &str, public_key: Option<String>, record: bool) -> StartSession {
+        StartSession { login: login.into(), public_key, record, command: None, ttl_minutes: None }
+    }
+
+    async fn read_until(client: &mut tokio::io::DuplexStream, received: &mut Vec<u8>, end: &[u8]) {
+        let mut buf = [0u8; 256];
+        while !received.ends_with(end) {
+            let n = client.read(&mut buf).await.unwrap();
+            assert!(n > 0, "stream closed before {:?}", String::from_utf8_lossy(end));
+            received.extend_from_slice(&buf[..n]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_direct_session_certificate() {
+        let broker = new_broker().await;
+        let (tenant, user) = (Uuid::new_v4(), Uuid::new_v4());
+        let (operator_key, _) = ephemeral_key().unwrap();
+        let now = Utc::now();
+        let request = StartSession {
+            command: Some("sudo journalctl -fu app".into()),
+            ttl_minutes: Some(10),
+            ..start("ec2-user", Some(operator_key.clone()), false)
+        };
+        let grant = broker.start_session(tenant, user, "web", "blue", "i-1", request, now).await.unwrap();
+        let ConnectionDetails::Direct { host, port, certificate, .. } = grant.connection else {
+            panic!("expected a direct session");
+        };
+        assert_eq!((host.as_str(), port), ("203.0.113.7", SSH_PORT));
+
+        let cert = SshCertificate::parse(&certificate).unwrap();
+        assert_eq!(cert.principals, ["ec2-user"]);
+        assert_eq!(cert.public_key, operator_key);
+        assert_eq!(cert.force_command(), Some("sudo journalctl -fu app"));
+        assert!(cert.key_id.ends_with(&grant.session.id.to_string()));
+        assert!(cert.valid_before <= now + Duration::minutes(10) + Duration::seconds(1));
+        assert!(cert.valid_before > now + Duration::minutes(9));
+        assert!(cert.signed_by(&broker.trusted_ca_keys().await.unwrap()[0]).unwrap());
+        assert_eq!(grant.session.certificate_serial, Some(cert.serial.to_string()));
+        assert_eq!(grant.session.disclosure, None);
+
+        let denied = broker.start_session(tenant, user, "web", "blue", "i-1", start("root", Some(operator_key.clone()), false), now);
+        assert!(matches!(denied.await, Err(AppError::Forbidden(_))));
+        let stopped = broker.start_session(tenant, user, "web", "blue", "i-2", start("ec2-user", Some(operator_key.clone()), false), now);
+        assert!(matches!(stopped.await, Err(AppError::Validation(_))));
+        let too_long = StartSession { ttl_minutes: Some(MAX_CERTIFICATE_TTL_MINUTES + 1), ..start("ec2-user", Some(operator_key.clone()), false) };
+        assert!(matches!(broker.start_session(tenant, user, "web", "blue", "i-1", too_long, now).await, Err(AppError::Validation(_))));
+        // Recording can't be requested until it's configured, and can't be skipped once required
+        let recorded = broker.start_session(tenant, user, "web", "blue", "i-1", start("ec2-user", None, true), now);
+        assert!(matches!(recorded.await, Err(AppError::Configuration(_))));
+        let strict = new_broker().await.with_required_recording();
+        let direct = strict.start_session(tenant, user, "web", "blue", "i-1", start("ec2-user", Some(operator_key), false), now);
+        assert!(matches!(direct.await, Err(AppError::Forbidden(_))));
+    }
+
+    #[tokio::test]
+    async fn test_recorded_session_replay_matches_stream() {
+        let dir = tempfile::tempdir().unwrap();
+        let recordings: Arc<dyn ObjectStore> = Arc::new(LocalFileStore::new(dir.path()));
+        let shell = Arc::new(EchoShell::default());
+        let broker = Arc::new(
+            new_broker()
+                .await
+                .with_recording(recordings.clone(), shell.clone())
+                .with_proxy_addresses(vec!["198.51.100.10/32".into()]),
+        );
+        let (tenant, user) = (Uuid::new_v4(), Uuid::new_v4());
+        let grant = broker
+            .start_session(tenant, user, "web", "blue", "i-1", start("ubuntu", None, true), Utc::now())
+            .await
+            .unwrap();
+        assert!(matches!(grant.connection, ConnectionDetails::Recorded { .. }));
+        assert_eq!(grant.session.disclosure.as_deref(), Some(RECORDING_DISCLOSURE));
+        let id = grant.session.id;
+
+        // Someone else's attach is refused
+        let (_, stranger) = tokio::io::duplex(1024);
+        assert!(matches!(broker.attach(tenant, Uuid::new_v4(), id, stranger).await, Err(AppError::NotFound(_))));
+
+        let (mut client, proxied) = tokio::io::duplex(1024);
+        let attached = tokio::spawn({
+            let broker = broker.clone();
+            async move { broker.attach(tenant, user, id, proxied).await }
+        });
+        let mut received = Vec::new();
+        read_until(&mut client, &mut received, b"$ ").await;
+        assert!(String::from_utf8_lossy(&received).starts_with(&format!("*** {} ***", RECORDING_DISCLOSURE)));
+        for line in [b"whoami\n".as_slice(), b"uptime\n", b"exit\n"] {
+            client.write_all(line).await.unwrap();
+            let mut expected = line.to_ascii_uppercase();
+            expected.extend_from_slice(b"$ ");
+            read_until(&mut client, &mut received, &expected).await;
+        }
+        client.shutdown().await.unwrap();
+        client.read_to_end(&mut received).await.unwrap();
+        let session = attached.await.unwrap().unwrap();
+        assert_eq!(session.status, SessionStatus::Closed);
+
+        // The proxy's own certificate is pinned to the proxy and barely outlives the handshake
+        let (target, cert) = shell.connections.lock().unwrap()[0].clone();
+        assert_eq!((target.host.as_str(), target.login.as_str()), ("10.0.0.7", "ubuntu"));
+        assert_eq!(cert.principals, ["ubuntu"]);
+        assert_eq!(cert.critical_options["source-address"], "198.51.100.10/32");
+        assert!(cert.valid_before - cert.valid_after <= Duration::minutes(PROXY_CERT_MINUTES + 1));
+        assert_eq!(session.certificate_serial, Some(cert.serial.to_string()));
+
+        let replay = broker.replay(id).await.unwrap();
+        assert_eq!(replay.output().unwrap(), received);
+        assert_eq!(replay.input().unwrap(), b"whoami\nuptime\nexit\n");
+        assert!(replay.chunks.windows(2).all(|w| w[0].offset_ms <= w[1].offset_ms));
+
+        // Rewriting a keystroke in storage, even with a fresh object checksum, breaks the chain
+        let summary = session.recording.unwrap();
+        let key = format!("{}/000001.ndjson", summary.key_prefix);
+        let (_, mut stream) = recordings.get(&key).await.unwrap();
+        let mut stored = Vec::new();
+        while let Some(part) = stream.next().await {
+            stored.extend_from_slice(&part.unwrap());
+        }
+        let forged = String::from_utf8(stored)
+            .unwrap()
+            .replace(&BASE64.encode(b"uptime\n"), &BASE64.encode(b"reboot\n"));
+        recordings.put(&key, forged.into(), &PutOptions::new()).await.unwrap();
+        assert!(matches!(broker.replay(id).await, Err(AppError::Internal(_))));
+    }
+
+    #[tokio::test]
+    async fn test_admin_terminates_recorded_session() {
+        let dir = tempfile::tempdir().unwrap();
+        let broker = Arc::new(
+            new_broker()
+                .await
+                .with_recording(Arc::new(LocalFileStore::new(dir.path())), Arc::new(EchoShell::default())),
+        );
+        let (tenant, user, admin) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let grant = broker
+            .start_session(tenant, user, "web", "blue", "i-1", start("ubuntu", None, true), Utc::now())
+            .await
+            .unwrap();
+        let id = grant.session.id;
+        let (mut client, proxied) = tokio::io::duplex(1024);
+        let attached = tokio::spawn({
+            let broker = broker.clone();
+            async move { broker.attach(tenant, user, id, proxied).await }
+        });
+        let mut received = Vec::new();
+        read_until(&mut client, &mut received, b"$ ").await;
+
+        let active = SessionFilter { status: Some(SessionStatus::Active), ..Default::default() };
+        assert_eq!(broker.list(&active, Utc::now(), 50).await.unwrap().len(), 1);
+        broker.terminate(id, admin, Utc::now()).await.unwrap();
+        let session = attached.await.unwrap().unwrap();
+        client.read_to_end(&mut received).await.unwrap();
+
+        assert_eq!((session.status, session.terminated_by), (SessionStatus::Terminated, Some(admin)));
+        assert!(String::from_utf8_lossy(&received).ends_with("terminated by an administrator ***\r\n"));
+        assert_eq!(broker.replay(id).await.unwrap().output().unwrap(), received);
+        assert!(broker.list(&active, Utc::now(), 50).await.unwrap().is_empty());
+        assert!(matches!(broker.terminate(id, admin, Utc::now()).await, Err(AppError::Validation(_))));
+        // A terminated session can't be attached again
+        let (_, again) = tokio::io::duplex(1024);
+        assert!(broker.attach(tenant, user, id, again).await.is_err());
+    }
+}
@@ -0,0 +1,223 @@
+use std::sync::Arc;
+
+use axum::body::Bytes;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sirsi_object_store::{ObjectStore, PutOptions};
+use tokio::time::Instant;
+use uuid::Uuid;
+
+use crate::error::{AppError, AppResult};
+
+// Chunks are buffered and written out as one object per segment of about this size
+pub const SEGMENT_BYTES: usize = 64 * 1024;
+const GENESIS: &str = "sirsi-ssh-recording:v1:";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StreamDirection {
+    // Keystrokes from the operator
+    Input,
+    // Terminal output from the instance
+    Output,
+}
+
+impl StreamDirection {
+    fn tag(self) -> u8 {
+        match self {
+            StreamDirection::Input => b'i',
+            StreamDirection::Output => b'o',
+        }
+    }
+}
+
+// One read from either side of the session. Each chunk's hash covers the previous chunk's hash,
+// so removing, reordering or editing a chunk breaks every hash after it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedChunk {
+    pub seq: u64,
+    // Since the session was attached
+    pub offset_ms: u64,
+    pub direction: StreamDirection,
+    // Base64
+    pub data: String,
+    pub hash: String,
+}
+
+impl RecordedChunk {
+    pub fn bytes(&self) -> AppResult<Vec<u8>> {
+        BASE64
+            .decode(&self.data)
+            .map_err(|_| AppError::Serialization(format!("Chunk {} of the recording is not valid base64", self.seq)))
+    }
+}
+
+// Where a recording lives and the hash of its last stored chunk. Saved with the session after
+// every segment, so the stored objects can be checked against it independently of the store.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordingSummary {
+    pub key_prefix: String,
+    pub segments: u32,
+    pub chunks: u64,
+    pub bytes: u64,
+    pub head: String,
+}
+
+fn genesis(session_id: Uuid) -> String {
+    format!("{:x}", Sha256::digest(format!("{}{}", GENESIS, session_id)))
+}
+
+fn chain(previous: &str, seq: u64, offset_ms: u64, direction: StreamDirection, data: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(previous.as_bytes());
+    hasher.update(seq.to_be_bytes());
+    hasher.update(offset_ms.to_be_bytes());
+    hasher.update([direction.tag()]);
+    hasher.update(data);
+    format!("{:x}", hasher.finalize())
+}
+
+fn segment_key(prefix: &str, segment: u32) -> String {
+    format!("{}/{:06}.ndjson", prefix, segment)
+}
+
+pub fn recording_prefix(session_id: Uuid) -> String {
+    format!("ssh-sessions/{}", session_id)
+}
+
+// Appends a session's traffic to the object store as newline-delimited JSON chunks
+pub struct Recorder {
+    store: Arc<dyn ObjectStore>,
+    session_id: Uuid,
+    started: Instant,
+    pending: Vec<u8>,
+    pending_chunks: u64,
+    pending_bytes: u64,
+    seq: u64,
+    head: String,
+    summary: RecordingSummary,
+}
+
+impl Recorder {
+    pub fn new(store: Arc<dyn ObjectStore>, session_id: Uuid) -> Self {
+        let head = genesis(session_id);
+        Self {
+            store,
+            session_id,
+            started: Instant::now(),
+            pending: Vec::new(),
+            pending_chunks: 0,
+            pending_bytes: 0,
+            seq: 0,
+            summary: RecordingSummary { key_prefix: recording_prefix(session_id), head: head.clone(), ..Default::default() },
+            head,
+        }
+    }
+
+    // What has been written to the store so far
+    pub fn summary(&self) -> &RecordingSummary {
+        &self.summary
+    }
+
+    // True when a segment was written, so the caller can save the new summary
+    pub async fn record(&mut self, direction: StreamDirection, data: &[u8]) -> AppResult<bool> {
+        let offset_ms = self.started.elapsed().as_millis() as u64;
+        let hash = chain(&self.head, self.seq, offset_ms, direction, data);
+        let chunk = RecordedChunk { seq: self.seq, offset_ms, direction, data: BASE64.encode(data), hash: hash.clone() };
+        serde_json::to_writer(&mut self.pending, &chunk).map_err(|e| AppError::Serialization(e.to_string()))?;
+        self.pending.push(b'\n');
+        self.seq += 1;
+        self.head = hash;
+        self.pending_chunks += 1;
+        self.pending_bytes += data.len() as u64;
+        if self.pending.len() >= SEGMENT_BYTES {
+            return self.flush().await;
+        }
+        Ok(false)
+    }
+
+    pub async fn flush(&mut self) -> AppResult<bool> {
+        if self.pending.is_empty() {
+            return Ok(false);
+        }
+        let segment = self.summary.segments + 1;
+        let options = PutOptions::new()
+            .with_content_type("application/x-ndjson")
+            .with_metadata("session", self.session_id.to_string())
+            .with_metadata("head", self.head.clone());
+        let data = Bytes::from(std::mem::take(&mut self.pending));
+        self.store
+            .put(&segment_key(&self.summary.key_prefix, segment), data, &options)
+            .await
+            .map_err(AppError::from_provider)?;
+        self.summary.segments = segment;
+        self.summary.chunks += std::mem::take(&mut self.pending_chunks);
+        self.summary.bytes += std::mem::take(&mut self.pending_bytes);
+        self.summary.head = self.head.clone();
+        Ok(true)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Replay {
+    pub chunks: Vec<RecordedChunk>,
+}
+
+impl Replay {
+    fn stream(&self, direction: StreamDirection) -> AppResult<Vec<u8>> {
+        let mut bytes = Vec::new();
+        for chunk in self.chunks.iter().filter(|c| c.direction == direction) {
+            bytes.extend(chunk.bytes()?);
+        }
+        Ok(bytes)
+    }
+
+    pub fn input(&self) -> AppResult<Vec<u8>> {
+        self.stream(StreamDirection::Input)
+    }
+
+    pub fn output(&self) -> AppResult<Vec<u8>> {
+        self.stream(StreamDirection::Output)
+    }
+}
+
+fn tampered(session_id: Uuid, reason: impl std::fmt::Display) -> AppError {
+    AppError::Internal(format!("Recording of session {} failed verification: {}", session_id, reason))
+}
+
+// Reads a recording back, checking every chunk against the hash chain and the chain's end
+// against `summary.head`
+pub async fn replay(store: &dyn ObjectStore, session_id: Uuid, summary: &RecordingSummary) -> AppResult<Replay> {
+    let mut chunks = Vec::new();
+    let mut head = genesis(session_id);
+    for segment in 1..=summary.segments {
+        let (_, mut stream) = store
+            .get(&segment_key(&summary.key_prefix, segment))
+            .await
+            .map_err(AppError::from_provider)?;
+        let mut data = Vec::new();
+        while let Some(part) = stream.next().await {
+            data.extend_from_slice(&part.map_err(AppError::from_provider)?);
+        }
+        for line in data.split(|b| *b == b'\n').filter(|line| !line.is_empty()) {
+            let chunk: RecordedChunk = serde_json::from_slice(line)
+                .map_err(|e| tampered(session_id, format!("unreadable chunk in segment {}: {}", segment, e)))?;
+            if chunk.seq != chunks.len() as u64 {
+                return Err(tampered(session_id, format!("expected chunk {}, found {}", chunks.len(), chunk.seq)));
+            }
+            let expected = chain(&head, chunk.seq, chunk.offset_ms, chunk.direction, &chunk.bytes()?);
+            if expected != chunk.hash {
+                return Err(tampered(session_id, format!("chunk {} doesn't match its hash", chunk.seq)));
+            }
+            head = chunk.hash.clone();
+            chunks.push(chunk);
+        }
+    }
+    if head != summary.head || chunks.len() as u64 != summary.chunks {
+        return Err(tampered(session_id, "the stored chunks don't end where the session's record does"));
+    }
+    Ok(Replay { chunks })
+}
@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::types::Json;
+use sqlx::{PgPool, Postgres, QueryBuilder}; // CockroachDB uses PostgreSQL protocol
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use super::{RecordingSummary, SessionFilter, SessionMode, SessionStatus, SshSession, SshSessionStore};
+use crate::error::{AppError, AppResult};
+
+const SESSION_COLUMNS: &str = "id, tenant_id, user_id, fleet_id, group_id, instance_id, host, login, mode, status, \
+    command, certificate_serial, disclosure, created_at, expires_at, attached_at, ended_at, terminated_by, recording";
+
+#[derive(sqlx::FromRow)]
+struct SessionRow {
+    id: Uuid,
+    tenant_id: Uuid,
+    user_id: Uuid,
+    fleet_id: String,
+    group_id: String,
+    instance_id: String,
+    host: String,
+    login: String,
+    mode: SessionMode,
+    status: SessionStatus,
+    command: Option<String>,
+    certificate_serial: Option<String>,
+    disclosure: Option<String>,
+    created_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+    attached_at: Option<DateTime<Utc>>,
+    ended_at: Option<DateTime<Utc>>,
+    terminated_by: Option<Uuid>,
+    recording: Option<Json<RecordingSummary>>,
+}
+
+impl From<SessionRow> for SshSession {
+    fn from(row: SessionRow) -> Self {
+        Self {
+            id: row.id,
+            tenant_id: row.tenant_id,
+            user_id: row.user_id,
+            fleet_id: row.fleet_id,
+            group_id: row.group_id,
+            instance_id: row.instance_id,
+            host: row.host,
+            login: row.login,
+            mode: row.mode,
+            status: row.status,
+            command: row.command,
+            certificate_serial: row.certificate_serial,
+            disclosure: row.disclosure,
+            created_at: row.created_at,
+            expires_at: row.expires_at,
+            attached_at: row.attached_at,
+            ended_at: row.ended_at,
+            terminated_by: row.terminated_by,
+            recording: row.recording.map(|r| r.0),
+        }
+    }
+}
+
+pub struct PgSshSessionStore {
+    pool: PgPool,
+}
+
+impl PgSshSessionStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl SshSessionStore for PgSshSessionStore {
+    async fn insert(&self, session: &SshSession) -> AppResult<()> {
+        sqlx::query(&format!(
+            r#"INSERT INTO ssh_sessions ({})
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19)"#,
+            SESSION_COLUMNS
+        ))
+        .bind(session.id)
+        .bind(session.tenant_id)
+        .bind(session.user_id)
+        .bind(&session.fleet_id)
+        .bind(&session.group_id)
+        .bind(&session.instance_id)
+        .bind(&session.host)
+        .bind(&session.login)
+        .bind(session.mode)
+        .bind(session.status)
+        .bind(&session.command)
+        .bind(&session.certificate_serial)
+        .bind(&session.disclosure)
+        .bind(session.created_at)
+        .bind(session.expires_at)
+        .bind(session.attached_at)
+        .bind(session.ended_at)
+        .bind(session.terminated_by)
+        .bind(session.recording.as_ref().map(Json))
+        .execute(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(())
+    }
+
+    async fn get(&self, id: Uuid) -> AppResult<Option<SshSession>> {
+        let row = sqlx::query_as::<_, SessionRow>(&format!("SELECT {} FROM ssh_sessions WHERE id = $1", SESSION_COLUMNS))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(row.map(SshSession::from))
+    }
+
+    async fn list(&self, filter: &SessionFilter, now: DateTime<Utc>, limit: i64) -> AppResult<Vec<SshSession>> {
+        let mut query = QueryBuilder::<Postgres>::new(format!("SELECT {} FROM ssh_sessions WHERE TRUE", SESSION_COLUMNS));
+        if let Some(tenant_id) = filter.tenant_id {
+            query.push(" AND tenant_id = ").push_bind(tenant_id);
+        }
+        match filter.status {
+            Some(SessionStatus::Active) => {
+                query
+                    .push(" AND status = 'active' AND (attached_at IS NOT NULL OR expires_at > ")
+                    .push_bind(now)
+                    .push(")");
+            }
+            Some(SessionStatus::Expired) => {
+                query
+                    .push(" AND status = 'active' AND attached_at IS NULL AND expires_at <= ")
+                    .push_bind(now);
+            }
+            Some(status) => {
+                query.push(" AND status = ").push_bind(status);
+            }
+            None => {}
+        }
+        query.push(" ORDER BY created_at DESC LIMIT ").push_bind(limit);
+        let rows = query
+            .build_query_as::<SessionRow>()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(rows.into_iter().map(|row| SshSession::from(row).effective(now)).collect())
+    }
+
+    async fn mark_attached(&self, id: Uuid, certificate_serial: &str, at: DateTime<Utc>) -> AppResult<bool> {
+        let result = sqlx::query(
+            r#"UPDATE ssh_sessions SET attached_at = $2, certificate_serial = $3
+            WHERE id = $1 AND status = 'active' AND attached_at IS NULL AND expires_at > $2"#
+        )
+        .bind(id)
+        .bind(at)
+        .bind(certificate_serial)
+        .execute(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn save_recording(&self, id: Uuid, recording: &RecordingSummary) -> AppResult<()> {
+        sqlx::query("UPDATE ssh_sessions SET recording = $2 WHERE id = $1")
+            .bind(id)
+            .bind(Json(recording))
+            .execute(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(())
+    }
+
+    async fn end(&self, id: Uuid, status: SessionStatus, at: DateTime<Utc>, by: Option<Uuid>) -> AppResult<bool> {
+        let result = sqlx::query(
+            r#"UPDATE ssh_sessions SET status = $2, ended_at = $3, terminated_by = $4
+            WHERE id = $1 AND status = 'active'"#
+        )
+        .bind(id)
+        .bind(status)
+        .bind(at)
+        .bind(by)
+        .execute(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(result.rows_affected() == 1)
+    }
+}
+
+#[derive(Default)]
+pub struct InMemorySshSessionStore {
+    sessions: Mutex<HashMap<Uuid, SshSession>>,
+}
+
+impl InMemorySshSessionStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl SshSessionStore for InMemorySshSessionStore {
+    async fn insert(&self, session: &SshSession) -> AppResult<()> {
+        self.sessions.lock().await.insert(session.id, session.clone());
+        Ok(())
+    }
+
+    async fn get(&self, id: Uuid) -> AppResult<Option<SshSession>> {
+        Ok(self.sessions.lock().await.get(&id).cloned())
+    }
+
+    async fn list(&self, filter: &SessionFilter, now: DateTime<Utc>, limit: i64) -> AppResult<Vec<SshSession>> {
+        let mut sessions: Vec<SshSession> = self
+            .sessions
+            .lock()
+            .await
+            .values()
+            .filter(|s| filter.tenant_id.is_none_or(|t| s.tenant_id == t))
+            .map(|s| s.clone().effective(now))
+            .filter(|s| filter.status.is_none_or(|status| s.status == status))
+            .collect();
+        sessions.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        sessions.truncate(limit.max(0) as usize);
+        Ok(sessions)
+    }
+
+    async fn mark_attached(&self, id: Uuid, certificate_serial: &str, at: DateTime<Utc>) -> AppResult<bool> {
+        let mut sessions = self.sessions.lock().await;
+        match sessions.get_mut(&id) {
+            Some(s) if s.status == SessionStatus::Active && s.attached_at.is_none() && s.expires_at > at => {
+                s.attached_at = Some(at);
+                s.certificate_serial = Some(certificate_serial.to_string());
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn save_recording(&self, id: Uuid, recording: &RecordingSummary) -> AppResult<()> {
+        if let Some(session) = self.sessions.lock().await.get_mut(&id) {
+            session.recording = Some(recording.clone());
+        }
+        Ok(())
+    }
+
+    async fn end(&self, id: Uuid, status: SessionStatus, at: DateTime<Utc>, by: Option<Uuid>) -> AppResult<bool> {
+        let mut sessions = self.sessions.lock().await;
+        match sessions.get_mut(&id) {
+            Some(s) if s.status == SessionStatus::Active => {
+                s.status = status;
+                s.ended_at = Some(at);
+                s.terminated_by = by;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+}
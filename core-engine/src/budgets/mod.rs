@@ -0,0 +1,710 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration as StdDuration;
+
+use axum::async_trait;
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::error::{AppError, AppResult};
+use crate::metering::{MeteringService, ResourceScope};
+use crate::reporting::render::{escape, money};
+use crate::reporting::{CostPoint, Recipient, ReportMessage, ReportNotifier};
+
+pub mod store;
+
+pub use store::{InMemoryBudgetStore, PgBudgetStore};
+
+const DEFAULT_THRESHOLDS: &[u32] = &[50, 80, 100];
+const MAX_THRESHOLD: u32 = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "VARCHAR", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum EnforcementKind {
+    StopNonProductionFleets,
+    BlockResourceCreation,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Budget {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub name: String,
+    pub scope: ResourceScope,
+    pub monthly_amount: f64,
+    pub currency: String,
+    // Percentages of the monthly amount, ascending
+    pub thresholds: Vec<u32>,
+    pub recipients: Vec<Recipient>,
+    // Applied once the month's actual spend reaches the full amount
+    pub enforcement: Option<EnforcementKind>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewBudget {
+    pub name: String,
+    pub scope: ResourceScope,
+    pub monthly_amount: f64,
+    #[serde(default = "default_currency")]
+    pub currency: String,
+    // 50, 80 and 100 when omitted
+    #[serde(default)]
+    pub thresholds: Vec<u32>,
+    #[serde(default)]
+    pub recipients: Vec<Recipient>,
+    pub enforcement: Option<EnforcementKind>,
+}
+
+fn default_currency() -> String {
+    "USD".to_string()
+}
+
+// A calendar month in UTC, `[start, end)`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct BudgetPeriod {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+}
+
+impl BudgetPeriod {
+    pub fn containing(day: NaiveDate) -> Self {
+        let start = day.with_day(1).unwrap_or(day);
+        let end = if start.month() == 12 {
+            NaiveDate::from_ymd_opt(start.year() + 1, 1, 1)
+        } else {
+            NaiveDate::from_ymd_opt(start.year(), start.month() + 1, 1)
+        }
+        .unwrap_or(start);
+        Self { start, end }
+    }
+
+    pub fn days(&self) -> i64 {
+        (self.end - self.start).num_days()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Forecast {
+    pub period: BudgetPeriod,
+    pub spent: f64,
+    pub daily_rate: f64,
+    pub projected: f64,
+    pub days_elapsed: i64,
+}
+
+// Linear burn rate over the month's complete days. Today's partial cost counts towards spend
+// but not the rate; on the first of the month there is no rate yet and the projection is the spend.
+pub fn forecast(costs: &[CostPoint], period: BudgetPeriod, today: NaiveDate) -> Forecast {
+    let today = today.clamp(period.start, period.end - Duration::days(1));
+    let in_period = costs.iter().filter(|c| c.day >= period.start && c.day <= today);
+    let spent: f64 = in_period.clone().map(|c| c.amount).sum();
+    let complete: f64 = in_period.filter(|c| c.day < today).map(|c| c.amount).sum();
+    let days_elapsed = (today - period.start).num_days();
+    let daily_rate = if days_elapsed > 0 { complete / days_elapsed as f64 } else { 0.0 };
+    let projected = (complete + daily_rate * (period.days() - days_elapsed) as f64).max(spent);
+    Forecast { period, spent, daily_rate, projected, days_elapsed }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "VARCHAR", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum AlertKind {
+    // Actual spend crossed the threshold
+    Actual,
+    // The month is on track to cross it
+    Forecast,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BudgetAlert {
+    pub budget_id: Uuid,
+    pub tenant_id: Uuid,
+    pub period_start: NaiveDate,
+    pub kind: AlertKind,
+    pub threshold: i32,
+    pub spent: f64,
+    pub projected: f64,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "VARCHAR", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum EnforcementStatus {
+    Active,
+    Failed,
+    Overridden,
+}
+
+// One per budget per month; rows are kept after an override so the log stays complete
+#[derive(Debug, Clone, PartialEq, Serialize, sqlx::FromRow)]
+pub struct Enforcement {
+    pub id: Uuid,
+    pub budget_id: Uuid,
+    pub tenant_id: Uuid,
+    pub period_start: NaiveDate,
+    pub kind: EnforcementKind,
+    pub status: EnforcementStatus,
+    pub spent: f64,
+    // What the hook needs to undo the action
+    pub state: Value,
+    pub error: Option<String>,
+    pub applied_at: DateTime<Utc>,
+    pub overridden_by: Option<Uuid>,
+    pub overridden_at: Option<DateTime<Utc>>,
+    pub override_reason: Option<String>,
+}
+
+#[async_trait]
+pub trait BudgetStore: Send + Sync {
+    async fn create(&self, budget: &Budget) -> AppResult<()>;
+    async fn list(&self, tenant_id: Uuid) -> AppResult<Vec<Budget>>;
+    async fn all(&self) -> AppResult<Vec<Budget>>;
+    async fn get(&self, tenant_id: Uuid, id: Uuid) -> AppResult<Option<Budget>>;
+    async fn delete(&self, tenant_id: Uuid, id: Uuid) -> AppResult<bool>;
+    // False when the alert was already recorded for the budget, month, kind and threshold
+    async fn record_alert(&self, alert: &BudgetAlert) -> AppResult<bool>;
+    async fn alerts(&self, tenant_id: Uuid, budget_id: Uuid, period_start: NaiveDate) -> AppResult<Vec<BudgetAlert>>;
+    // False when the budget was already enforced this month, whatever became of it
+    async fn claim_enforcement(&self, enforcement: &Enforcement) -> AppResult<bool>;
+    async fn update_enforcement(&self, enforcement: &Enforcement) -> AppResult<()>;
+    async fn enforcement(&self, id: Uuid) -> AppResult<Option<Enforcement>>;
+    // Newest first; every budget when `budget_id` is `None`
+    async fn enforcements(&self, tenant_id: Uuid, budget_id: Option<Uuid>) -> AppResult<Vec<Enforcement>>;
+}
+
+// Daily costs for a scope, from wherever cost data is ingested; `until` is inclusive
+#[async_trait]
+pub trait CostSource: Send + Sync {
+    async fn daily_costs(
+        &self,
+        tenant_id: Uuid,
+        scope: &ResourceScope,
+        since: NaiveDate,
+        until: NaiveDate,
+    ) -> AppResult<Vec<CostPoint>>;
+}
+
+// An action taken when a budget is exhausted. `apply` returns the state `revert` needs.
+#[async_trait]
+pub trait EnforcementHook: Send + Sync {
+    fn kind(&self) -> EnforcementKind;
+    async fn apply(&self, budget: &Budget, now: DateTime<Utc>) -> AppResult<Value>;
+    async fn revert(&self, tenant_id: Uuid, state: &Value) -> AppResult<()>;
+}
+
+// Blocks new resources in the budget's scope through the quota layer
+pub struct CreationBlockHook {
+    metering: Arc<MeteringService>,
+}
+
+impl CreationBlockHook {
+    pub fn new(metering: Arc<MeteringService>) -> Self {
+        Self { metering }
+    }
+}
+
+#[async_trait]
+impl EnforcementHook for CreationBlockHook {
+    fn kind(&self) -> EnforcementKind {
+        EnforcementKind::BlockResourceCreation
+    }
+
+    async fn apply(&self, budget: &Budget, now: DateTime<Utc>) -> AppResult<Value> {
+        let reason = format!("budget \"{}\" is exhausted for this month", budget.name);
+        let block = self.metering.block_creation(budget.tenant_id, budget.scope.clone(), reason, now).await?;
+        Ok(json!({ "block_id": block.id }))
+    }
+
+    async fn revert(&self, tenant_id: Uuid, state: &Value) -> AppResult<()> {
+        let block_id = state
+            .get("block_id")
+            .and_then(Value::as_str)
+            .and_then(|id| id.parse::<Uuid>().ok())
+            .ok_or_else(|| AppError::Internal("Enforcement has no creation block to lift".into()))?;
+        self.metering.lift_creation_block(tenant_id, block_id).await?;
+        Ok(())
+    }
+}
+
+// What the fleet lifecycle needs to expose for budget enforcement; production fleets are never stopped
+#[async_trait]
+pub trait FleetLifecycle: Send + Sync {
+    // Returns the fleets it stopped
+    async fn stop_non_production(&self, tenant_id: Uuid, scope: &ResourceScope) -> AppResult<Vec<String>>;
+    async fn start(&self, tenant_id: Uuid, fleets: &[String]) -> AppResult<()>;
+}
+
+pub struct FleetStopHook {
+    fleets: Arc<dyn FleetLifecycle>,
+}
+
+impl FleetStopHook {
+    pub fn new(fleets: Arc<dyn FleetLifecycle>) -> Self {
+        Self { fleets }
+    }
+}
+
+#[async_trait]
+impl EnforcementHook for FleetStopHook {
+    fn kind(&self) -> EnforcementKind {
+        EnforcementKind::StopNonProductionFleets
+    }
+
+    async fn apply(&self, budget: &Budget, _now: DateTime<Utc>) -> AppResult<Value> {
+        let stopped = self.fleets.stop_non_production(budget.tenant_id, &budget.scope).await?;
+        Ok(json!({ "fleets": stopped }))
+    }
+
+    async fn revert(&self, tenant_id: Uuid, state: &Value) -> AppResult<()> {
+        let fleets: Vec<String> = state
+            .get("fleets")
+            .cloned()
+            .map(serde_json::from_value)
+            .transpose()
+            .map_err(|e| AppError::Internal(format!("Unreadable enforcement state: {}", e)))?
+            .unwrap_or_default();
+        self.fleets.start(tenant_id, &fleets).await
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BudgetStatus {
+    pub budget: Budget,
+    pub forecast: Forecast,
+    pub percent_spent: f64,
+    pub percent_projected: f64,
+    pub alerts: Vec<BudgetAlert>,
+    pub enforcement: Option<Enforcement>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Evaluation {
+    pub alerts: Vec<BudgetAlert>,
+    pub enforcement: Option<Enforcement>,
+}
+
+pub struct BudgetService {
+    store: Arc<dyn BudgetStore>,
+    costs: Option<Arc<dyn CostSource>>,
+    notifier: Option<Arc<dyn ReportNotifier>>,
+    hooks: HashMap<EnforcementKind, Arc<dyn EnforcementHook>>,
+}
+
+impl BudgetService {
+    pub fn new(store: Arc<dyn BudgetStore>) -> Self {
+        Self { store, costs: None, notifier: None, hooks: HashMap::new() }
+    }
+
+    pub fn with_costs(mut self, costs: Arc<dyn CostSource>) -> Self {
+        self.costs = Some(costs);
+        self
+    }
+
+    // Alerts go out over the same email and Slack channels as scheduled reports
+    pub fn with_notifier(mut self, notifier: Arc<dyn ReportNotifier>) -> Self {
+        self.notifier = Some(notifier);
+        self
+    }
+
+    pub fn with_hook(mut self, hook: Arc<dyn EnforcementHook>) -> Self {
+        self.hooks.insert(hook.kind(), hook);
+        self
+    }
+
+    pub async fn create(&self, tenant_id: Uuid, budget: NewBudget, now: DateTime<Utc>) -> AppResult<Budget> {
+        if budget.name.trim().is_empty() {
+            return Err(AppError::Validation("Budget name is required".into()));
+        }
+        if !budget.monthly_amount.is_finite() || budget.monthly_amount <= 0.0 {
+            return Err(AppError::Validation("Monthly amount must be greater than zero".into()));
+        }
+        if let ResourceScope::Tag { key, value } = &budget.scope {
+            if key.trim().is_empty() || value.trim().is_empty() {
+                return Err(AppError::Validation("Tag scopes need a key and a value".into()));
+            }
+        }
+        if budget.thresholds.iter().any(|t| *t == 0 || *t > MAX_THRESHOLD) {
+            return Err(AppError::Validation(format!("Thresholds must be between 1 and {} percent", MAX_THRESHOLD)));
+        }
+        let blank = budget.recipients.iter().any(|r| match r {
+            Recipient::Email { address } => !address.contains('@'),
+            Recipient::Slack { channel_id } => channel_id.trim().is_empty(),
+        });
+        if blank {
+            return Err(AppError::Validation("Recipients need an email address or Slack channel id".into()));
+        }
+        if let Some(kind) = budget.enforcement {
+            if !self.hooks.contains_key(&kind) {
+                return Err(AppError::Validation(format!("Enforcement {:?} is not available", kind)));
+            }
+        }
+        let mut thresholds = if budget.thresholds.is_empty() { DEFAULT_THRESHOLDS.to_vec() } else { budget.thresholds };
+        thresholds.sort_unstable();
+        thresholds.dedup();
+        let budget = Budget {
+            id: Uuid::new_v4(),
+            tenant_id,
+            name: budget.name,
+            scope: budget.scope,
+            monthly_amount: budget.monthly_amount,
+            currency: budget.currency,
+            thresholds,
+            recipients: budget.recipients,
+            enforcement: budget.enforcement,
+            created_at: now,
+        };
+        self.store.create(&budget).await?;
+        Ok(budget)
+    }
+
+    pub async fn budgets(&self, tenant_id: Uuid) -> AppResult<Vec<Budget>> {
+        self.store.list(tenant_id).await
+    }
+
+    pub async fn budget(&self, tenant_id: Uuid, id: Uuid) -> AppResult<Budget> {
+        self.store
+            .get(tenant_id, id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Budget not found".into()))
+    }
+
+    // An active enforcement has to be overridden first, so nothing is left blocked or stopped
+    pub async fn delete(&self, tenant_id: Uuid, id: Uuid) -> AppResult<()> {
+        self.budget(tenant_id, id).await?;
+        let enforcements = self.store.enforcements(tenant_id, Some(id)).await?;
+        if let Some(active) = enforcements.iter().find(|e| e.status == EnforcementStatus::Active) {
+            return Err(AppError::Conflict {
+                message: "Budget has an active enforcement; override it before deleting the budget".into(),
+                current: Some(json!({ "enforcement_id": active.id })),
+            });
+        }
+        self.store.delete(tenant_id, id).await?;
+        Ok(())
+    }
+
+    pub async fn status(&self, tenant_id: Uuid, id: Uuid, now: DateTime<Utc>) -> AppResult<BudgetStatus> {
+        let budget = self.budget(tenant_id, id).await?;
+        let forecast = self.forecast(&budget, now).await?;
+        let alerts = self.store.alerts(tenant_id, id, forecast.period.start).await?;
+        let enforcement = self
+            .store
+            .enforcements(tenant_id, Some(id))
+            .await?
+            .into_iter()
+            .find(|e| e.period_start == forecast.period.start);
+        Ok(BudgetStatus {
+            percent_spent: forecast.spent / budget.monthly_amount * 100.0,
+            percent_projected: forecast.projected / budget.monthly_amount * 100.0,
+            budget,
+            forecast,
+            alerts,
+            enforcement,
+        })
+    }
+
+    pub async fn enforcements(&self, tenant_id: Uuid, budget_id: Option<Uuid>) -> AppResult<Vec<Enforcement>> {
+        self.store.enforcements(tenant_id, budget_id).await
+    }
+
+    async fn forecast(&self, budget: &Budget, now: DateTime<Utc>) -> AppResult<Forecast> {
+        let costs = self
+            .costs
+            .as_ref()
+            .ok_or_else(|| AppError::Configuration("No cost source configured for budgets".into()))?;
+        let today = now.date_naive();
+        let period = BudgetPeriod::containing(today);
+        let points = costs.daily_costs(budget.tenant_id, &budget.scope, period.start, today).await?;
+        Ok(forecast(&points, period, today))
+    }
+
+    // Sends each threshold alert once per month and enforces once the full amount is spent
+    pub async fn evaluate(&self, budget: &Budget, now: DateTime<Utc>) -> AppResult<Evaluation> {
+        let forecast = self.forecast(budget, now).await?;
+        let mut evaluation = Evaluation::default();
+        let mut due = Vec::new();
+        for threshold in &budget.thresholds {
+            if forecast.spent >= budget.monthly_amount * *threshold as f64 / 100.0 {
+                due.push((AlertKind::Actual, *threshold));
+            }
+        }
+        if forecast.spent < budget.monthly_amount && forecast.projected >= budget.monthly_amount {
+            due.push((AlertKind::Forecast, 100));
+        }
+        for (kind, threshold) in due {
+            let alert = BudgetAlert {
+                budget_id: budget.id,
+                tenant_id: budget.tenant_id,
+                period_start: forecast.period.start,
+                kind,
+                threshold: threshold as i32,
+                spent: forecast.spent,
+                projected: forecast.projected,
+                created_at: now,
+            };
+            if self.store.record_alert(&alert).await? {
+                self.notify(budget, &alert_message(budget, &alert)).await;
+                evaluation.alerts.push(alert);
+            }
+        }
+
+        if let Some(kind) = budget.enforcement {
+            if forecast.spent >= budget.monthly_amount {
+                evaluation.enforcement = self.enforce(budget, kind, &forecast, now).await?;
+            }
+        }
+        Ok(evaluation)
+    }
+
+    async fn enforce(
+        &self,
+        budget: &Budget,
+        kind: EnforcementKind,
+        forecast: &Forecast,
+        now: DateTime<Utc>,
+    ) -> AppResult<Option<Enforcement>> {
+        let mut enforcement = Enforcement {
+            id: Uuid::new_v4(),
+            budget_id: budget.id,
+            tenant_id: budget.tenant_id,
+            period_start: forecast.period.start,
+            kind,
+            status: EnforcementStatus::Active,
+            spent: forecast.spent,
+            state: Value::Null,
+            error: None,
+            applied_at: now,
+            overridden_by: None,
+            overridden_at: None,
+            override_reason: None,
+        };
+        // Claimed before the hook runs, so concurrent evaluators cannot both apply it
+        if !self.store.claim_enforcement(&enforcement).await? {
+            return Ok(None);
+        }
+        let result = match self.hooks.get(&kind) {
+            Some(hook) => hook.apply(budget, now).await,
+            None => Err(AppError::Configuration(format!("No {:?} enforcement hook configured", kind))),
+        };
+        match result {
+            Ok(state) => {
+                info!("Enforced {:?} for budget {} of tenant {}", kind, budget.id, budget.tenant_id);
+                enforcement.state = state;
+            }
+            Err(e) => {
+                error!("Failed to enforce {:?} for budget {}: {}", kind, budget.id, e);
+                enforcement.status = EnforcementStatus::Failed;
+                enforcement.error = Some(e.to_string());
+            }
+        }
+        self.store.update_enforcement(&enforcement).await?;
+        self.notify(budget, &enforcement_message(budget, &enforcement)).await;
+        Ok(Some(enforcement))
+    }
+
+    // Reverts the hook's action; the budget stays unenforced for the rest of the month
+    pub async fn override_enforcement(
+        &self,
+        id: Uuid,
+        admin_id: Uuid,
+        reason: Option<String>,
+        now: DateTime<Utc>,
+    ) -> AppResult<Enforcement> {
+        let mut enforcement = self
+            .store
+            .enforcement(id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Enforcement not found".into()))?;
+        if enforcement.status != EnforcementStatus::Active {
+            return Err(AppError::Conflict {
+                message: "Only active enforcements can be overridden".into(),
+                current: serde_json::to_value(&enforcement).ok(),
+            });
+        }
+        let hook = self
+            .hooks
+            .get(&enforcement.kind)
+            .ok_or_else(|| AppError::Configuration(format!("No {:?} enforcement hook configured", enforcement.kind)))?;
+        hook.revert(enforcement.tenant_id, &enforcement.state).await?;
+        enforcement.status = EnforcementStatus::Overridden;
+        enforcement.overridden_by = Some(admin_id);
+        enforcement.overridden_at = Some(now);
+        enforcement.override_reason = reason;
+        self.store.update_enforcement(&enforcement).await?;
+        info!("Enforcement {} overridden by {}", id, admin_id);
+        Ok(enforcement)
+    }
+
+    pub async fn evaluate_all(&self, now: DateTime<Utc>) -> AppResult<usize> {
+        let mut evaluated = 0;
+        for budget in self.store.all().await? {
+            match self.evaluate(&budget, now).await {
+                Ok(_) => evaluated += 1,
+                Err(e) => warn!("Failed to evaluate budget {}: {}", budget.id, e),
+            }
+        }
+        Ok(evaluated)
+    }
+
+    pub fn spawn_evaluator(self: Arc<Self>, interval: StdDuration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = self.evaluate_all(Utc::now()).await {
+                    warn!("Failed to evaluate budgets: {}", e);
+                }
+            }
+        })
+    }
+
+    async fn notify(&self, budget: &Budget, message: &ReportMessage) {
+        for recipient in &budget.recipients {
+            let result = match &self.notifier {
+                Some(notifier) => notifier.deliver(recipient, message).await,
+                None => Err(AppError::Configuration("No notifier configured for budgets".into())),
+            };
+            if let Err(e) = result {
+                warn!("Failed to notify {:?} about budget {}: {}", recipient, budget.id, e);
+            }
+        }
+    }
+}
+
+fn alert_message(budget: &Budget, alert: &BudgetAlert) -> ReportMessage {
+    let (subject, line) = match alert.kind {
+        AlertKind::Actual => (
+            format!("{}: {}% of the monthly budget spent", budget.name, alert.threshold),
+            format!("Spend this month has reached {}%", alert.threshold),
+        ),
+        AlertKind::Forecast => (
+            format!("{}: on track to exceed the monthly budget", budget.name),
+            "At the current burn rate this month's spend will exceed the budget".to_string(),
+        ),
+    };
+    let html = format!(
+        "<h1>{}</h1><p>{}: {} spent and {} projected of {}.</p>",
+        escape(&budget.name),
+        line,
+        money(alert.spent, &budget.currency),
+        money(alert.projected, &budget.currency),
+        money(budget.monthly_amount, &budget.currency),
+    );
+    ReportMessage { subject, html, attachments: Vec::new() }
+}
+
+fn enforcement_message(budget: &Budget, enforcement: &Enforcement) -> ReportMessage {
+    let action = match enforcement.kind {
+        EnforcementKind::StopNonProductionFleets => "Non-production fleets have been stopped",
+        EnforcementKind::BlockResourceCreation => "New resources have been blocked",
+    };
+    let outcome = match &enforcement.error {
+        Some(e) => format!("Enforcement failed: {}", escape(e)),
+        None => format!("{} until an administrator overrides the enforcement.", action),
+    };
+    ReportMessage {
+        subject: format!("{}: monthly budget exhausted", budget.name),
+        html: format!("<h1>{}</h1><p>{}</p>", escape(&budget.name), outcome),
+        attachments: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metering::InMemoryUsageStore;
+    use chrono::TimeZone;
+    use std::sync::Mutex;
+
+    struct FixedCosts(Mutex<Vec<CostPoint>>);
+
+    #[async_trait]
+    impl CostSource for FixedCosts {
+        async fn daily_costs(&self, _: Uuid, _: &ResourceScope, since: NaiveDate, until: NaiveDate) -> AppResult<Vec<CostPoint>> {
+            Ok(self.0.lock().unwrap().iter().filter(|c| c.day >= since && c.day <= until).cloned().collect())
+        }
+    }
+
+    fn june(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 6, day).unwrap()
+    }
+
+    fn costs(days: std::ops::RangeInclusive<u32>, amount: f64) -> Vec<CostPoint> {
+        days.map(|d| CostPoint { day: june(d), amount }).collect()
+    }
+
+    #[test]
+    fn test_forecast_mid_month() {
+        let period = BudgetPeriod::containing(june(16));
+        assert_eq!((period.start, period.end, period.days()), (june(1), NaiveDate::from_ymd_opt(2025, 7, 1).unwrap(), 30));
+
+        // Fifteen complete days at 10 a day, plus a partial 3 so far today
+        let mut points = costs(1..=15, 10.0);
+        points.push(CostPoint { day: june(16), amount: 3.0 });
+        points.push(CostPoint { day: NaiveDate::from_ymd_opt(2025, 5, 31).unwrap(), amount: 500.0 });
+        let f = forecast(&points, period, june(16));
+        assert_eq!(f.days_elapsed, 15);
+        assert!((f.spent - 153.0).abs() < 1e-9);
+        assert!((f.daily_rate - 10.0).abs() < 1e-9);
+        assert!((f.projected - 300.0).abs() < 1e-9);
+
+        // No complete days yet on the first
+        let f = forecast(&[CostPoint { day: june(1), amount: 40.0 }], period, june(1));
+        assert_eq!((f.daily_rate, f.projected), (0.0, 40.0));
+    }
+
+    #[tokio::test]
+    async fn test_enforcement_fires_once_per_breach() {
+        let metering = Arc::new(MeteringService::new(Arc::new(InMemoryUsageStore::new())));
+        let source = Arc::new(FixedCosts(Mutex::new(costs(1..=10, 10.0))));
+        let service = BudgetService::new(Arc::new(InMemoryBudgetStore::new()))
+            .with_costs(source.clone())
+            .with_hook(Arc::new(CreationBlockHook::new(metering.clone())));
+        let (tenant, project) = (Uuid::new_v4(), Uuid::new_v4());
+        let now = Utc.with_ymd_and_hms(2025, 6, 11, 12, 0, 0).unwrap();
+        let new = NewBudget {
+            name: "staging".into(),
+            scope: ResourceScope::Project { project_id: project },
+            monthly_amount: 150.0,
+            currency: default_currency(),
+            thresholds: Vec::new(),
+            recipients: Vec::new(),
+            enforcement: Some(EnforcementKind::BlockResourceCreation),
+        };
+        let budget = service.create(tenant, new, now).await.unwrap();
+        assert_eq!(budget.thresholds, vec![50, 80, 100]);
+
+        // 100 of 150 spent, 300 projected: the 50% alert and a forecast warning
+        let evaluation = service.evaluate(&budget, now).await.unwrap();
+        let kinds: Vec<_> = evaluation.alerts.iter().map(|a| (a.kind, a.threshold)).collect();
+        assert_eq!(kinds, vec![(AlertKind::Actual, 50), (AlertKind::Forecast, 100)]);
+        assert!(evaluation.enforcement.is_none());
+
+        source.0.lock().unwrap().extend(costs(11..=16, 10.0));
+        let later = now + Duration::days(6);
+        let first = service.evaluate(&budget, later).await.unwrap();
+        let enforcement = first.enforcement.unwrap();
+        assert_eq!(enforcement.status, EnforcementStatus::Active);
+        assert_eq!(first.alerts.len(), 2);
+        assert!(service.evaluate(&budget, later).await.unwrap().enforcement.is_none());
+        assert_eq!(metering.creation_blocks(tenant).await.unwrap().len(), 1);
+        assert!(metering.check_creation(tenant, project, &json!({})).await.is_err());
+        assert!(metering.check_creation(tenant, Uuid::new_v4(), &json!({})).await.is_ok());
+        assert!(matches!(service.delete(tenant, budget.id).await, Err(AppError::Conflict { .. })));
+
+        let admin = Uuid::new_v4();
+        let overridden = service.override_enforcement(enforcement.id, admin, None, later).await.unwrap();
+        assert_eq!((overridden.status, overridden.overridden_by), (EnforcementStatus::Overridden, Some(admin)));
+        assert!(metering.check_creation(tenant, project, &json!({})).await.is_ok());
+        assert!(service.override_enforcement(enforcement.id, admin, None, later).await.is_err());
+        // Still over budget, but the override holds for the rest of the month
+        assert!(service.evaluate(&budget, later + Duration::days(1)).await.unwrap().enforcement.is_none());
+        assert_eq!(service.enforcements(tenant, Some(budget.id)).await.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,375 @@
+use axum::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use sqlx::types::Json;
+use sqlx::PgPool; // CockroachDB uses PostgreSQL protocol
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use super::{AlertKind, Budget, BudgetAlert, BudgetStore, Enforcement, EnforcementKind};
+use crate::error::{AppError, AppResult};
+use crate::metering::ResourceScope;
+use crate::reporting::Recipient;
+
+#[derive(sqlx::FromRow)]
+struct BudgetRow {
+    id: Uuid,
+    tenant_id: Uuid,
+    name: String,
+    scope: Json<ResourceScope>,
+    monthly_amount: f64,
+    currency: String,
+    thresholds: Json<Vec<u32>>,
+    recipients: Json<Vec<Recipient>>,
+    enforcement: Option<EnforcementKind>,
+    created_at: DateTime<Utc>,
+}
+
+impl From<BudgetRow> for Budget {
+    fn from(row: BudgetRow) -> Self {
+        Self {
+            id: row.id,
+            tenant_id: row.tenant_id,
+            name: row.name,
+            scope: row.scope.0,
+            monthly_amount: row.monthly_amount,
+            currency: row.currency,
+            thresholds: row.thresholds.0,
+            recipients: row.recipients.0,
+            enforcement: row.enforcement,
+            created_at: row.created_at,
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct AlertRow {
+    budget_id: Uuid,
+    tenant_id: Uuid,
+    period_start: NaiveDate,
+    kind: AlertKind,
+    threshold: i32,
+    spent: f64,
+    projected: f64,
+    created_at: DateTime<Utc>,
+}
+
+impl From<AlertRow> for BudgetAlert {
+    fn from(row: AlertRow) -> Self {
+        Self {
+            budget_id: row.budget_id,
+            tenant_id: row.tenant_id,
+            period_start: row.period_start,
+            kind: row.kind,
+            threshold: row.threshold,
+            spent: row.spent,
+            projected: row.projected,
+            created_at: row.created_at,
+        }
+    }
+}
+
+const BUDGET_COLUMNS: &str =
+    "id, tenant_id, name, scope, monthly_amount, currency, thresholds, recipients, enforcement, created_at";
+const ALERT_COLUMNS: &str = "budget_id, tenant_id, period_start, kind, threshold, spent, projected, created_at";
+const ENFORCEMENT_COLUMNS: &str = "id, budget_id, tenant_id, period_start, kind, status, spent, state, error, \
+    applied_at, overridden_by, overridden_at, override_reason";
+
+pub struct PgBudgetStore {
+    pool: PgPool,
+}
+
+impl PgBudgetStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl BudgetStore for PgBudgetStore {
+    async fn create(&self, budget: &Budget) -> AppResult<()> {
+        let sql = format!("INSERT INTO budgets ({}) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)", BUDGET_COLUMNS);
+        sqlx::query(&sql)
+            .bind(budget.id)
+            .bind(budget.tenant_id)
+            .bind(&budget.name)
+            .bind(Json(&budget.scope))
+            .bind(budget.monthly_amount)
+            .bind(&budget.currency)
+            .bind(Json(&budget.thresholds))
+            .bind(Json(&budget.recipients))
+            .bind(budget.enforcement)
+            .bind(budget.created_at)
+            .execute(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(())
+    }
+
+    async fn list(&self, tenant_id: Uuid) -> AppResult<Vec<Budget>> {
+        let sql = format!("SELECT {} FROM budgets WHERE tenant_id = $1 ORDER BY created_at", BUDGET_COLUMNS);
+        let rows = sqlx::query_as::<_, BudgetRow>(&sql)
+            .bind(tenant_id)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(rows.into_iter().map(Budget::from).collect())
+    }
+
+    async fn all(&self) -> AppResult<Vec<Budget>> {
+        let sql = format!("SELECT {} FROM budgets ORDER BY tenant_id, created_at", BUDGET_COLUMNS);
+        let rows = sqlx::query_as::<_, BudgetRow>(&sql)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(rows.into_iter().map(Budget::from).collect())
+    }
+
+    async fn get(&self, tenant_id: Uuid, id: Uuid) -> AppResult<Option<Budget>> {
+        let sql = format!("SELECT {} FROM budgets WHERE tenant_id = $1 AND id = $2", BUDGET_COLUMNS);
+        let row = sqlx::query_as::<_, BudgetRow>(&sql)
+            .bind(tenant_id)
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(row.map(Budget::from))
+    }
+
+    async fn delete(&self, tenant_id: Uuid, id: Uuid) -> AppResult<bool> {
+        let result = sqlx::query("DELETE FROM budgets WHERE tenant_id = $1 AND id = $2")
+            .bind(tenant_id)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn record_alert(&self, alert: &BudgetAlert) -> AppResult<bool> {
+        let sql = format!(
+            "INSERT INTO budget_alerts ({}) VALUES ($1, $2, $3, $4, $5, $6, $7, $8) ON CONFLICT DO NOTHING",
+            ALERT_COLUMNS
+        );
+        let result = sqlx::query(&sql)
+            .bind(alert.budget_id)
+            .bind(alert.tenant_id)
+            .bind(alert.period_start)
+            .bind(alert.kind)
+            .bind(alert.threshold)
+            .bind(alert.spent)
+            .bind(alert.projected)
+            .bind(alert.created_at)
+            .execute(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn alerts(&self, tenant_id: Uuid, budget_id: Uuid, period_start: NaiveDate) -> AppResult<Vec<BudgetAlert>> {
+        let sql = format!(
+            "SELECT {} FROM budget_alerts WHERE tenant_id = $1 AND budget_id = $2 AND period_start = $3 ORDER BY created_at, threshold",
+            ALERT_COLUMNS
+        );
+        let rows = sqlx::query_as::<_, AlertRow>(&sql)
+            .bind(tenant_id)
+            .bind(budget_id)
+            .bind(period_start)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(rows.into_iter().map(BudgetAlert::from).collect())
+    }
+
+    async fn claim_enforcement(&self, enforcement: &Enforcement) -> AppResult<bool> {
+        let sql = format!(
+            "INSERT INTO budget_enforcements ({}) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13) \
+            ON CONFLICT (budget_id, period_start) DO NOTHING",
+            ENFORCEMENT_COLUMNS
+        );
+        let result = sqlx::query(&sql)
+            .bind(enforcement.id)
+            .bind(enforcement.budget_id)
+            .bind(enforcement.tenant_id)
+            .bind(enforcement.period_start)
+            .bind(enforcement.kind)
+            .bind(enforcement.status)
+            .bind(enforcement.spent)
+            .bind(&enforcement.state)
+            .bind(&enforcement.error)
+            .bind(enforcement.applied_at)
+            .bind(enforcement.overridden_by)
+            .bind(enforcement.overridden_at)
+            .bind(&enforcement.override_reason)
+            .execute(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn update_enforcement(&self, enforcement: &Enforcement) -> AppResult<()> {
+        sqlx::query(
+            r#"UPDATE budget_enforcements
+            SET status = $2, state = $3, error = $4, overridden_by = $5, overridden_at = $6, override_reason = $7
+            WHERE id = $1"#
+        )
+        .bind(enforcement.id)
+        .bind(enforcement.status)
+        .bind(&enforcement.state)
+        .bind(&enforcement.error)
+        .bind(enforcement.overridden_by)
+        .bind(enforcement.overridden_at)
+        .bind(&enforcement.override_reason)
+        .execute(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(())
+    }
+
+    async fn enforcement(&self, id: Uuid) -> AppResult<Option<Enforcement>> {
+        let sql = format!("SELECT {} FROM budget_enforcements WHERE id = $1", ENFORCEMENT_COLUMNS);
+        let enforcement = sqlx::query_as::<_, Enforcement>(&sql)
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(enforcement)
+    }
+
+    async fn enforcements(&self, tenant_id: Uuid, budget_id: Option<Uuid>) -> AppResult<Vec<Enforcement>> {
+        let sql = format!(
+            "SELECT {} FROM budget_enforcements WHERE tenant_id = $1 AND ($2::UUID IS NULL OR budget_id = $2) \
+            ORDER BY applied_at DESC",
+            ENFORCEMENT_COLUMNS
+        );
+        let enforcements = sqlx::query_as::<_, Enforcement>(&sql)
+            .bind(tenant_id)
+            .bind(budget_id)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(enforcements)
+    }
+}
+
+#[derive(Default)]
+struct InMemoryState {
+    budgets: Vec<Budget>,
+    alerts: Vec<BudgetAlert>,
+    enforcements: Vec<Enforcement>,
+}
+
+#[derive(Default)]
+pub struct InMemoryBudgetStore {
+    state: Mutex<InMemoryState>,
+}
+
+impl InMemoryBudgetStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl BudgetStore for InMemoryBudgetStore {
+    async fn create(&self, budget: &Budget) -> AppResult<()> {
+        self.state.lock().await.budgets.push(budget.clone());
+        Ok(())
+    }
+
+    async fn list(&self, tenant_id: Uuid) -> AppResult<Vec<Budget>> {
+        Ok(self.state.lock().await.budgets.iter().filter(|b| b.tenant_id == tenant_id).cloned().collect())
+    }
+
+    async fn all(&self) -> AppResult<Vec<Budget>> {
+        Ok(self.state.lock().await.budgets.clone())
+    }
+
+    async fn get(&self, tenant_id: Uuid, id: Uuid) -> AppResult<Option<Budget>> {
+        let state = self.state.lock().await;
+        Ok(state.budgets.iter().find(|b| b.tenant_id == tenant_id && b.id == id).cloned())
+    }
+
+    async fn delete(&self, tenant_id: Uuid, id: Uuid) -> AppResult<bool> {
+        let mut state = self.state.lock().await;
+        let before = state.budgets.len();
+        state.budgets.retain(|b| !(b.tenant_id == tenant_id && b.id == id));
+        let deleted = state.budgets.len() < before;
+        if deleted {
+            state.alerts.retain(|a| a.budget_id != id);
+        }
+        Ok(deleted)
+    }
+
+    async fn record_alert(&self, alert: &BudgetAlert) -> AppResult<bool> {
+        let mut state = self.state.lock().await;
+        let seen = state.alerts.iter().any(|a| {
+            a.budget_id == alert.budget_id
+                && a.period_start == alert.period_start
+                && a.kind == alert.kind
+                && a.threshold == alert.threshold
+        });
+        if seen {
+            return Ok(false);
+        }
+        state.alerts.push(alert.clone());
+        Ok(true)
+    }
+
+    async fn alerts(&self, tenant_id: Uuid, budget_id: Uuid, period_start: NaiveDate) -> AppResult<Vec<BudgetAlert>> {
+        let state = self.state.lock().await;
+        Ok(state
+            .alerts
+            .iter()
+            .filter(|a| a.tenant_id == tenant_id && a.budget_id == budget_id && a.period_start == period_start)
+            .cloned()
+            .collect())
+    }
+
+    async fn claim_enforcement(&self, enforcement: &Enforcement) -> AppResult<bool> {
+        let mut state = self.state.lock().await;
+        let claimed = state
+            .enforcements
+            .iter()
+            .any(|e| e.budget_id == enforcement.budget_id && e.period_start == enforcement.period_start);
+        if claimed {
+            return Ok(false);
+        }
+        state.enforcements.push(enforcement.clone());
+        Ok(true)
+    }
+
+    async fn update_enforcement(&self, enforcement: &Enforcement) -> AppResult<()> {
+        let mut state = self.state.lock().await;
+        if let Some(stored) = state.enforcements.iter_mut().find(|e| e.id == enforcement.id) {
+            *stored = enforcement.clone();
+        }
+        Ok(())
+    }
+
+    async fn enforcement(&self, id: Uuid) -> AppResult<Option<Enforcement>> {
+        Ok(self.state.lock().await.enforcements.iter().find(|e| e.id == id).cloned())
+    }
+
+    async fn enforcements(&self, tenant_id: Uuid, budget_id: Option<Uuid>) -> AppResult<Vec<Enforcement>> {
+        let state = self.state.lock().await;
+        let mut enforcements: Vec<Enforcement> = state
+            .enforcements
+            .iter()
+            .filter(|e| e.tenant_id == tenant_id && budget_id.is_none_or(|id| e.budget_id == id))
+            .cloned()
+            .collect();
+        enforcements.sort_by_key(|e| std::cmp::Reverse(e.applied_at));
+        Ok(enforcements)
+    }
+}
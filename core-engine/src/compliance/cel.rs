@@ -0,0 +1,754 @@
+// The subset of CEL (https://github.com/google/cel-spec) compliance rules are written in:
+// literals, lists and maps, field selection and indexing, `has()`, arithmetic, comparisons,
+// `in`, `&&`/`||`/`!`, `?:`, `size`, `contains`/`startsWith`/`endsWith`, `int`/`string`
+// conversions and the `exists`/`all`/`filter`/`map` macros. JSON values stand in for CEL values,
+// so ints and doubles compare equal when numerically equal.
+use std::cmp::Ordering;
+
+use serde_json::{Map, Number, Value};
+
+use crate::error::{AppError, AppResult};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Function {
+    Size,
+    Contains,
+    StartsWith,
+    EndsWith,
+    Int,
+    String,
+}
+
+impl Function {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "size" => Function::Size,
+            "contains" => Function::Contains,
+            "startsWith" => Function::StartsWith,
+            "endsWith" => Function::EndsWith,
+            "int" => Function::Int,
+            "string" => Function::String,
+            _ => return None,
+        })
+    }
+
+    // (global arguments, method arguments); None where the form isn't allowed
+    fn arity(&self) -> (Option<usize>, Option<usize>) {
+        match self {
+            Function::Size => (Some(1), Some(0)),
+            Function::Contains | Function::StartsWith | Function::EndsWith => (None, Some(1)),
+            Function::Int | Function::String => (Some(1), None),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Macro {
+    Exists,
+    All,
+    Filter,
+    Map,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinaryOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    In,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Literal(Value),
+    Ident(String),
+    List(Vec<Expr>),
+    Map(Vec<(Expr, Expr)>),
+    Select(Box<Expr>, String),
+    Has(Box<Expr>, String),
+    Index(Box<Expr>, Box<Expr>),
+    Call(Function, Vec<Expr>),
+    Comprehension(Macro, Box<Expr>, String, Box<Expr>),
+    Not(Box<Expr>),
+    Negate(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Conditional(Box<Expr>, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Int(i64),
+    Double(f64),
+    Str(String),
+    Ident(String),
+    Punct(&'static str),
+}
+
+const PUNCTUATION: &[&str] = &[
+    "&&", "||", "==", "!=", "<=", ">=", "<", ">", "!", "+", "-", "*", "/", "%", "?", ":", ".", ",", "(", ")", "[",
+    "]", "{", "}",
+];
+const RESERVED: &[&str] = &["true", "false", "null", "in"];
+
+fn invalid(position: usize, message: impl std::fmt::Display) -> AppError {
+    AppError::Validation(format!("Invalid expression at {}: {}", position, message))
+}
+
+fn tokenize(source: &str) -> AppResult<Vec<(usize, Token)>> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            let token = if text.contains('.') {
+                text.parse().map(Token::Double).map_err(|_| invalid(start, format!("bad number '{}'", text)))?
+            } else {
+                text.parse().map(Token::Int).map_err(|_| invalid(start, format!("bad number '{}'", text)))?
+            };
+            tokens.push((start, token));
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push((start, Token::Ident(chars[start..i].iter().collect())));
+        } else if c == '\'' || c == '"' {
+            let start = i;
+            let mut text = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return Err(invalid(start, "unterminated string")),
+                    Some(&q) if q == c => break,
+                    Some('\\') => {
+                        let escaped = match chars.get(i + 1) {
+                            Some('n') => '\n',
+                            Some('t') => '\t',
+                            Some(&e @ ('\\' | '\'' | '"')) => e,
+                            _ => return Err(invalid(i, "unknown escape")),
+                        };
+                        text.push(escaped);
+                        i += 2;
+                    }
+                    Some(&other) => {
+                        text.push(other);
+                        i += 1;
+                    }
+                }
+            }
+            i += 1;
+            tokens.push((start, Token::Str(text)));
+        } else {
+            let rest: String = chars[i..chars.len().min(i + 2)].iter().collect();
+            let punct = PUNCTUATION
+                .iter()
+                .find(|p| rest.starts_with(**p))
+                .ok_or_else(|| invalid(i, format!("unexpected '{}'", c)))?;
+            tokens.push((i, Token::Punct(punct)));
+            i += punct.len();
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    end: usize,
+    // Variables in scope; macros bind theirs while their body is parsed
+    bound: Vec<String>,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, t)| t)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens.get(self.pos).map_or(self.end, |(p, _)| *p)
+    }
+
+    fn eat(&mut self, punct: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Punct(p)) if *p == punct) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, punct: &str) -> AppResult<()> {
+        if self.eat(punct) {
+            Ok(())
+        } else {
+            Err(invalid(self.position(), format!("expected '{}'", punct)))
+        }
+    }
+
+    fn ident(&mut self) -> AppResult<String> {
+        match self.peek() {
+            Some(Token::Ident(name)) if !RESERVED.contains(&name.as_str()) => {
+                let name = name.clone();
+                self.pos += 1;
+                Ok(name)
+            }
+            _ => Err(invalid(self.position(), "expected a name")),
+        }
+    }
+
+    fn expr(&mut self) -> AppResult<Expr> {
+        let condition = self.or()?;
+        if self.eat("?") {
+            let then = self.expr()?;
+            self.expect(":")?;
+            let otherwise = self.expr()?;
+            return Ok(Expr::Conditional(Box::new(condition), Box::new(then), Box::new(otherwise)));
+        }
+        Ok(condition)
+    }
+
+    fn or(&mut self) -> AppResult<Expr> {
+        let mut left = self.and()?;
+        while self.eat("||") {
+            left = Expr::Or(Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> AppResult<Expr> {
+        let mut left = self.relation()?;
+        while self.eat("&&") {
+            left = Expr::And(Box::new(left), Box::new(self.relation()?));
+        }
+        Ok(left)
+    }
+
+    fn relation(&mut self) -> AppResult<Expr> {
+        let mut left = self.additive()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Punct("==")) => BinaryOp::Eq,
+                Some(Token::Punct("!=")) => BinaryOp::Ne,
+                Some(Token::Punct("<")) => BinaryOp::Lt,
+                Some(Token::Punct("<=")) => BinaryOp::Le,
+                Some(Token::Punct(">")) => BinaryOp::Gt,
+                Some(Token::Punct(">=")) => BinaryOp::Ge,
+                Some(Token::Ident(name)) if name == "in" => BinaryOp::In,
+                _ => return Ok(left),
+            };
+            self.pos += 1;
+            left = Expr::Binary(op, Box::new(left), Box::new(self.additive()?));
+        }
+    }
+
+    fn additive(&mut self) -> AppResult<Expr> {
+        let mut left = self.multiplicative()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Punct("+")) => BinaryOp::Add,
+                Some(Token::Punct("-")) => BinaryOp::Sub,
+                _ => return Ok(left),
+            };
+            self.pos += 1;
+            left = Expr::Binary(op, Box::new(left), Box::new(self.multiplicative()?));
+        }
+    }
+
+    fn multiplicative(&mut self) -> AppResult<Expr> {
+        let mut left = self.unary()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Punct("*")) => BinaryOp::Mul,
+                Some(Token::Punct("/")) => BinaryOp::Div,
+                Some(Token::Punct("%")) => BinaryOp::Rem,
+                _ => return Ok(left),
+            };
+            self.pos += 1;
+            left = Expr::Binary(op, Box::new(left), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> AppResult<Expr> {
+        if self.eat("!") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        if self.eat("-") {
+            return Ok(Expr::Negate(Box::new(self.unary()?)));
+        }
+        self.member()
+    }
+
+    fn member(&mut self) -> AppResult<Expr> {
+        let mut target = self.primary()?;
+        loop {
+            if self.eat(".") {
+                let at = self.position();
+                let name = self.ident()?;
+                if !self.eat("(") {
+                    target = Expr::Select(Box::new(target), name);
+                    continue;
+                }
+                let comprehension = match name.as_str() {
+                    "exists" => Some(Macro::Exists),
+                    "all" => Some(Macro::All),
+                    "filter" => Some(Macro::Filter),
+                    "map" => Some(Macro::Map),
+                    _ => None,
+                };
+                target = match comprehension {
+                    Some(kind) => {
+                        let var = self.ident()?;
+                        self.expect(",")?;
+                        self.bound.push(var.clone());
+                        let body = self.expr();
+                        self.bound.pop();
+                        self.expect(")")?;
+                        Expr::Comprehension(kind, Box::new(target), var, Box::new(body?))
+                    }
+                    None => {
+                        let function = Function::parse(&name).ok_or_else(|| invalid(at, format!("unknown function '{}'", name)))?;
+                        let args = self.args()?;
+                        if function.arity().1 != Some(args.len()) {
+                            return Err(invalid(at, format!("wrong number of arguments to '{}'", name)));
+                        }
+                        Expr::Call(function, std::iter::once(target).chain(args).collect())
+                    }
+                };
+            } else if self.eat("[") {
+                let index = self.expr()?;
+                self.expect("]")?;
+                target = Expr::Index(Box::new(target), Box::new(index));
+            } else {
+                return Ok(target);
+            }
+        }
+    }
+
+    fn args(&mut self) -> AppResult<Vec<Expr>> {
+        let mut args = Vec::new();
+        if self.eat(")") {
+            return Ok(args);
+        }
+        loop {
+            args.push(self.expr()?);
+            if self.eat(")") {
+                return Ok(args);
+            }
+            self.expect(",")?;
+        }
+    }
+
+    fn primary(&mut self) -> AppResult<Expr> {
+        let at = self.position();
+        let token = self.peek().cloned().ok_or_else(|| invalid(at, "unexpected end of expression"))?;
+        self.pos += 1;
+        match token {
+            Token::Int(n) => Ok(Expr::Literal(Value::from(n))),
+            Token::Double(n) => Ok(Expr::Literal(Value::from(n))),
+            Token::Str(s) => Ok(Expr::Literal(Value::String(s))),
+            Token::Ident(name) => match name.as_str() {
+                "true" => Ok(Expr::Literal(Value::Bool(true))),
+                "false" => Ok(Expr::Literal(Value::Bool(false))),
+                "null" => Ok(Expr::Literal(Value::Null)),
+                "has" if self.eat("(") => match self.expr()? {
+                    Expr::Select(target, field) => {
+                        self.expect(")")?;
+                        Ok(Expr::Has(target, field))
+                    }
+                    _ => Err(invalid(at, "has() takes a field selection like has(resource.tags.env)")),
+                },
+                _ if self.eat("(") => {
+                    let function = Function::parse(&name).ok_or_else(|| invalid(at, format!("unknown function '{}'", name)))?;
+                    let args = self.args()?;
+                    if function.arity().0 != Some(args.len()) {
+                        return Err(invalid(at, format!("wrong number of arguments to '{}'", name)));
+                    }
+                    Ok(Expr::Call(function, args))
+                }
+                _ if self.bound.contains(&name) => Ok(Expr::Ident(name)),
+                _ => Err(invalid(at, format!("unknown variable '{}'", name))),
+            },
+            Token::Punct("(") => {
+                let inner = self.expr()?;
+                self.expect(")")?;
+                Ok(inner)
+            }
+            Token::Punct("[") => {
+                let mut items = Vec::new();
+                if !self.eat("]") {
+                    loop {
+                        items.push(self.expr()?);
+                        if self.eat("]") {
+                            break;
+                        }
+                        self.expect(",")?;
+                    }
+                }
+                Ok(Expr::List(items))
+            }
+            Token::Punct("{") => {
+                let mut entries = Vec::new();
+                if !self.eat("}") {
+                    loop {
+                        let key = self.expr()?;
+                        self.expect(":")?;
+                        entries.push((key, self.expr()?));
+                        if self.eat("}") {
+                            break;
+                        }
+                        self.expect(",")?;
+                    }
+                }
+                Ok(Expr::Map(entries))
+            }
+            Token::Punct(p) => Err(invalid(at, format!("unexpected '{}'", p))),
+        }
+    }
+}
+
+fn eval_error(message: impl Into<String>) -> AppError {
+    AppError::Validation(message.into())
+}
+
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "bool",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "list",
+        Value::Object(_) => "map",
+    }
+}
+
+fn equals(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => x.as_f64() == y.as_f64(),
+        (Value::Array(x), Value::Array(y)) => x.len() == y.len() && x.iter().zip(y).all(|(x, y)| equals(x, y)),
+        (Value::Object(x), Value::Object(y)) => {
+            x.len() == y.len() && x.iter().all(|(k, v)| y.get(k).is_some_and(|w| equals(v, w)))
+        }
+        _ => a == b,
+    }
+}
+
+fn compare(a: &Value, b: &Value) -> AppResult<Ordering> {
+    let ordering = match (a, b) {
+        (Value::Number(x), Value::Number(y)) => x.as_f64().partial_cmp(&y.as_f64()),
+        (Value::String(x), Value::String(y)) => Some(x.cmp(y)),
+        (Value::Bool(x), Value::Bool(y)) => Some(x.cmp(y)),
+        _ => None,
+    };
+    ordering.ok_or_else(|| eval_error(format!("cannot compare {} with {}", kind(a), kind(b))))
+}
+
+fn arithmetic(op: BinaryOp, a: &Value, b: &Value) -> AppResult<Value> {
+    match (op, a, b) {
+        (BinaryOp::Add, Value::String(x), Value::String(y)) => return Ok(Value::String(format!("{}{}", x, y))),
+        (BinaryOp::Add, Value::Array(x), Value::Array(y)) => return Ok(Value::Array(x.iter().chain(y).cloned().collect())),
+        _ => {}
+    }
+    let (Value::Number(x), Value::Number(y)) = (a, b) else {
+        return Err(eval_error(format!("cannot apply {:?} to {} and {}", op, kind(a), kind(b))));
+    };
+    if let (Some(x), Some(y)) = (x.as_i64(), y.as_i64()) {
+        let result = match op {
+            BinaryOp::Add => x.checked_add(y),
+            BinaryOp::Sub => x.checked_sub(y),
+            BinaryOp::Mul => x.checked_mul(y),
+            BinaryOp::Div => x.checked_div(y),
+            BinaryOp::Rem => x.checked_rem(y),
+            _ => unreachable!(),
+        };
+        return result.map(Value::from).ok_or_else(|| eval_error("integer overflow or division by zero"));
+    }
+    let (x, y) = (x.as_f64().unwrap_or(f64::NAN), y.as_f64().unwrap_or(f64::NAN));
+    let result = match op {
+        BinaryOp::Add => x + y,
+        BinaryOp::Sub => x - y,
+        BinaryOp::Mul => x * y,
+        BinaryOp::Div => x / y,
+        BinaryOp::Rem => x % y,
+        _ => unreachable!(),
+    };
+    Number::from_f64(result).map(Value::Number).ok_or_else(|| eval_error("result is not a finite number"))
+}
+
+fn as_bool(value: Value) -> AppResult<bool> {
+    match value {
+        Value::Bool(b) => Ok(b),
+        other => Err(eval_error(format!("expected a bool, got {}", kind(&other)))),
+    }
+}
+
+fn as_str(value: &Value) -> AppResult<&str> {
+    value.as_str().ok_or_else(|| eval_error(format!("expected a string, got {}", kind(value))))
+}
+
+// A parsed expression; parsing rejects unknown variables, functions and arities, so what's left
+// at evaluation time is missing fields and type mismatches
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    root: Expr,
+}
+
+impl Program {
+    pub fn parse(source: &str, variables: &[&str]) -> AppResult<Self> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            end: source.chars().count(),
+            bound: variables.iter().map(|v| v.to_string()).collect(),
+        };
+        let root = parser.expr()?;
+        if parser.pos < parser.tokens.len() {
+            return Err(invalid(parser.position(), "unexpected trailing input"));
+        }
+        Ok(Self { root })
+    }
+
+    pub fn evaluate(&self, variables: &[(&str, Value)]) -> AppResult<Value> {
+        let mut scope: Vec<(String, Value)> = variables.iter().map(|(k, v)| (k.to_string(), v.clone())).collect();
+        eval(&self.root, &mut scope)
+    }
+
+    pub fn evaluate_bool(&self, variables: &[(&str, Value)]) -> AppResult<bool> {
+        as_bool(self.evaluate(variables)?)
+    }
+}
+
+fn eval(expr: &Expr, scope: &mut Vec<(String, Value)>) -> AppResult<Value> {
+    match expr {
+        Expr::Literal(value) => Ok(value.clone()),
+        Expr::Ident(name) => scope
+            .iter()
+            .rev()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.clone())
+            .ok_or_else(|| eval_error(format!("unbound variable '{}'", name))),
+        Expr::List(items) => Ok(Value::Array(items.iter().map(|i| eval(i, scope)).collect::<AppResult<_>>()?)),
+        Expr::Map(entries) => {
+            let mut map = Map::new();
+            for (key, value) in entries {
+                let key = eval(key, scope)?;
+                map.insert(as_str(&key)?.to_string(), eval(value, scope)?);
+            }
+            Ok(Value::Object(map))
+        }
+        Expr::Select(target, field) => match eval(target, scope)? {
+            Value::Object(mut map) => map.remove(field).ok_or_else(|| eval_error(format!("no such key '{}'", field))),
+            other => Err(eval_error(format!("cannot select '{}' from {}", field, kind(&other)))),
+        },
+        Expr::Has(target, field) => match eval(target, scope)? {
+            Value::Object(map) => Ok(Value::Bool(map.contains_key(field))),
+            other => Err(eval_error(format!("has() needs a map, got {}", kind(&other)))),
+        },
+        Expr::Index(target, index) => {
+            let (target, index) = (eval(target, scope)?, eval(index, scope)?);
+            match (&target, &index) {
+                (Value::Array(items), Value::Number(n)) => n
+                    .as_u64()
+                    .and_then(|n| items.get(n as usize))
+                    .cloned()
+                    .ok_or_else(|| eval_error("index out of range")),
+                (Value::Object(map), Value::String(key)) => {
+                    map.get(key).cloned().ok_or_else(|| eval_error(format!("no such key '{}'", key)))
+                }
+                _ => Err(eval_error(format!("cannot index {} with {}", kind(&target), kind(&index)))),
+            }
+        }
+        Expr::Call(function, args) => {
+            let args = args.iter().map(|a| eval(a, scope)).collect::<AppResult<Vec<_>>>()?;
+            call(*function, &args)
+        }
+        Expr::Comprehension(kind, range, var, body) => {
+            let items = match eval(range, scope)? {
+                Value::Array(items) => items,
+                Value::Object(map) => map.into_iter().map(|(k, _)| Value::String(k)).collect(),
+                other => return Err(eval_error(format!("cannot iterate over {}", self::kind(&other)))),
+            };
+            let mut results = Vec::new();
+            for item in items {
+                scope.push((var.clone(), item.clone()));
+                let result = eval(body, scope);
+                scope.pop();
+                if *kind == Macro::Map {
+                    results.push(result?);
+                    continue;
+                }
+                match (kind, as_bool(result?)?) {
+                    (Macro::Exists, true) => return Ok(Value::Bool(true)),
+                    (Macro::All, false) => return Ok(Value::Bool(false)),
+                    (Macro::Filter, true) => results.push(item),
+                    _ => {}
+                }
+            }
+            Ok(match kind {
+                Macro::Exists => Value::Bool(false),
+                Macro::All => Value::Bool(true),
+                Macro::Filter | Macro::Map => Value::Array(results),
+            })
+        }
+        Expr::Not(inner) => Ok(Value::Bool(!as_bool(eval(inner, scope)?)?)),
+        Expr::Negate(inner) => arithmetic(BinaryOp::Sub, &Value::from(0), &eval(inner, scope)?),
+        // As in CEL, a false operand decides `&&` (and a true one `||`) even if the other errors
+        Expr::And(left, right) => {
+            let left = eval(left, scope).and_then(as_bool);
+            if matches!(left, Ok(false)) {
+                return Ok(Value::Bool(false));
+            }
+            let right = eval(right, scope).and_then(as_bool);
+            match (left, right) {
+                (_, Ok(false)) => Ok(Value::Bool(false)),
+                (Err(e), _) | (_, Err(e)) => Err(e),
+                _ => Ok(Value::Bool(true)),
+            }
+        }
+        Expr::Or(left, right) => {
+            let left = eval(left, scope).and_then(as_bool);
+            if matches!(left, Ok(true)) {
+                return Ok(Value::Bool(true));
+            }
+            let right = eval(right, scope).and_then(as_bool);
+            match (left, right) {
+                (_, Ok(true)) => Ok(Value::Bool(true)),
+                (Err(e), _) | (_, Err(e)) => Err(e),
+                _ => Ok(Value::Bool(false)),
+            }
+        }
+        Expr::Conditional(condition, then, otherwise) => {
+            if as_bool(eval(condition, scope)?)? {
+                eval(then, scope)
+            } else {
+                eval(otherwise, scope)
+            }
+        }
+        Expr::Binary(op, left, right) => {
+            let (left, right) = (eval(left, scope)?, eval(right, scope)?);
+            match op {
+                BinaryOp::Eq => Ok(Value::Bool(equals(&left, &right))),
+                BinaryOp::Ne => Ok(Value::Bool(!equals(&left, &right))),
+                BinaryOp::Lt => Ok(Value::Bool(compare(&left, &right)?.is_lt())),
+                BinaryOp::Le => Ok(Value::Bool(compare(&left, &right)?.is_le())),
+                BinaryOp::Gt => Ok(Value::Bool(compare(&left, &right)?.is_gt())),
+                BinaryOp::Ge => Ok(Value::Bool(compare(&left, &right)?.is_ge())),
+                BinaryOp::In => match &right {
+                    Value::Array(items) => Ok(Value::Bool(items.iter().any(|i| equals(i, &left)))),
+                    Value::Object(map) => Ok(Value::Bool(map.contains_key(as_str(&left)?))),
+                    other => Err(eval_error(format!("'in' needs a list or map, got {}", kind(other)))),
+                },
+                _ => arithmetic(*op, &left, &right),
+            }
+        }
+    }
+}
+
+fn call(function: Function, args: &[Value]) -> AppResult<Value> {
+    match (function, args) {
+        (Function::Size, [value]) => match value {
+            Value::String(s) => Ok(Value::from(s.chars().count())),
+            Value::Array(items) => Ok(Value::from(items.len())),
+            Value::Object(map) => Ok(Value::from(map.len())),
+            other => Err(eval_error(format!("size() of {}", kind(other)))),
+        },
+        (Function::Contains, [target, needle]) => Ok(Value::Bool(as_str(target)?.contains(as_str(needle)?))),
+        (Function::StartsWith, [target, prefix]) => Ok(Value::Bool(as_str(target)?.starts_with(as_str(prefix)?))),
+        (Function::EndsWith, [target, suffix]) => Ok(Value::Bool(as_str(target)?.ends_with(as_str(suffix)?))),
+        (Function::Int, [value]) => match value {
+            Value::Number(n) => n
+                .as_i64()
+                .or_else(|| n.as_f64().filter(|f| f.is_finite()).map(|f| f.trunc() as i64))
+                .map(Value::from)
+                .ok_or_else(|| eval_error("int() out of range")),
+            Value::String(s) => s.trim().parse::<i64>().map(Value::from).map_err(|_| eval_error(format!("int('{}')", s))),
+            other => Err(eval_error(format!("int() of {}", kind(other)))),
+        },
+        (Function::String, [value]) => match value {
+            Value::String(s) => Ok(Value::String(s.clone())),
+            Value::Number(_) | Value::Bool(_) => Ok(Value::String(value.to_string())),
+            other => Err(eval_error(format!("string() of {}", kind(other)))),
+        },
+        _ => Err(eval_error(format!("wrong arguments to {:?}", function))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn check(source: &str, resource: Value) -> AppResult<Value> {
+        Program::parse(source, &["resource"])?.evaluate(&[("resource", resource)])
+    }
+
+    #[test]
+    fn test_evaluates_rule_expressions() {
+        let group = json!({
+            "type": "ec2:security-group",
+            "tags": {"env": "prod"},
+            "attributes": {"ingress": [
+                {"cidr": "10.0.0.0/8", "from_port": 0, "to_port": 65535},
+                {"cidr": "0.0.0.0/0", "from_port": "443", "to_port": 443},
+            ]},
+        });
+        let cases = [
+            ("resource.attributes.ingress.exists(r, r.cidr == '0.0.0.0/0' && int(r.from_port) <= 22 && r.to_port >= 22)", json!(false)),
+            ("resource.attributes.ingress.exists(r, r.cidr == '0.0.0.0/0' && int(r.from_port) <= 443)", json!(true)),
+            ("resource.attributes.ingress.all(r, r.cidr.startsWith('10.'))", json!(false)),
+            ("size(resource.attributes.ingress.filter(r, r.to_port > 1000)) == 1", json!(true)),
+            ("resource.attributes.ingress.map(r, r.cidr)[1]", json!("0.0.0.0/0")),
+            ("!has(resource.tags.owner) && 'env' in resource.tags", json!(true)),
+            ("resource.tags.env in ['prod', 'staging'] ? 2 * 3 + 1 : -1", json!(7)),
+            ("resource.type.endsWith(\"group\") && resource[\"tags\"].size() == 1", json!(true)),
+            // A missing field is an error unless the other side of && already decides it
+            ("false && resource.tags.owner == 'x'", json!(false)),
+            ("resource.tags.owner == 'x' && false", json!(false)),
+            ("1 == 1.0 && string(1.5) == '1.5'", json!(true)),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(check(source, group.clone()).unwrap(), expected, "{}", source);
+        }
+        assert!(check("resource.tags.owner == 'x'", group.clone()).is_err());
+        assert!(check("resource.tags < 3", group).is_err());
+    }
+
+    #[test]
+    fn test_rejects_malformed_expressions() {
+        let cases = [
+            "resource.tags.env ==",
+            "resource.tags.env = 'prod'",
+            "(resource.name",
+            "resource.name == 'unterminated",
+            "other.name == 'x'",
+            "resource.tags.exists(t, u == 'x')",
+            "resource.name.lower() == 'x'",
+            "has(resource)",
+            "size(resource.tags, 1) > 0",
+            "resource.name.contains()",
+            "resource.name == 'x' 'y'",
+        ];
+        for source in cases {
+            let error = Program::parse(source, &["resource"]).unwrap_err();
+            assert!(matches!(error, AppError::Validation(_)), "{}", source);
+        }
+    }
+}
@@ -0,0 +1,692 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::error::{AppError, AppResult};
+use crate::inventory::{InventoryPage, InventoryQuery, InventoryResource, InventoryService, MAX_PAGE_SIZE};
+
+pub mod cel;
+pub mod store;
+
+pub use cel::Program;
+pub use store::{InMemoryComplianceStore, PgComplianceStore};
+
+// Names rule expressions can refer to
+const VARIABLES: &[&str] = &["resource", "required_tags"];
+const BUILTIN_PREFIX: &str = "builtin.";
+const DEFAULT_REQUIRED_TAGS: &[&str] = &["owner", "environment"];
+const DEFAULT_PROJECT_TAG: &str = "project";
+const MAX_KEY_LEN: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Low => "low",
+            Severity::Medium => "medium",
+            Severity::High => "high",
+            Severity::Critical => "critical",
+        }
+    }
+
+    pub fn parse(value: &str) -> AppResult<Self> {
+        serde_json::from_value(Value::String(value.to_string()))
+            .map_err(|_| AppError::Validation(format!("Unknown severity '{}'", value)))
+    }
+}
+
+// `expression` is true for resources that violate the rule. It sees the inventory resource as
+// `resource` and the configured required tag names as `required_tags`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ComplianceRule {
+    pub key: String,
+    pub name: String,
+    pub description: String,
+    pub severity: Severity,
+    pub remediation: String,
+    // Every resource type when empty
+    pub resource_types: Vec<String>,
+    pub expression: String,
+    pub enabled: bool,
+    pub builtin: bool,
+}
+
+impl ComplianceRule {
+    fn applies_to(&self, resource: &InventoryResource) -> bool {
+        self.resource_types.is_empty() || self.resource_types.contains(&resource.resource_type)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewComplianceRule {
+    pub key: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub severity: Severity,
+    #[serde(default)]
+    pub remediation: String,
+    #[serde(default)]
+    pub resource_types: Vec<String>,
+    pub expression: String,
+}
+
+struct BuiltinRule {
+    key: &'static str,
+    name: &'static str,
+    severity: Severity,
+    remediation: &'static str,
+    resource_types: &'static [&'static str],
+    expression: &'static str,
+}
+
+// Discovery reports provider metadata as strings, hence the `string()` and `int()` conversions
+const BUILTIN_RULES: &[BuiltinRule] = &[
+    BuiltinRule {
+        key: "builtin.public-s3-bucket",
+        name: "S3 bucket is publicly accessible",
+        severity: Severity::Critical,
+        remediation: "Enable S3 Block Public Access on the bucket and remove public ACL grants.",
+        resource_types: &["s3:bucket"],
+        expression: "(has(resource.attributes.acl) && resource.attributes.acl in ['public-read', 'public-read-write']) \
+            || (has(resource.attributes.public) && string(resource.attributes.public) == 'true')",
+    },
+    BuiltinRule {
+        key: "builtin.open-ssh",
+        name: "Security group allows SSH from anywhere",
+        severity: Severity::High,
+        remediation: "Restrict port 22 ingress to known CIDR ranges or use a bastion or SSM Session Manager.",
+        resource_types: &["ec2:security-group"],
+        expression: "has(resource.attributes.ingress) && resource.attributes.ingress.exists(r, \
+            r.cidr in ['0.0.0.0/0', '::/0'] && int(r.from_port) <= 22 && int(r.to_port) >= 22)",
+    },
+    BuiltinRule {
+        key: "builtin.unencrypted-volume",
+        name: "Block storage volume is not encrypted",
+        severity: Severity::High,
+        remediation: "Snapshot the volume, copy the snapshot with encryption enabled and replace the volume.",
+        resource_types: &["ec2:volume", "compute.googleapis.com/Disk", "microsoft.compute/disks"],
+        expression: "!has(resource.attributes.encrypted) || string(resource.attributes.encrypted) != 'true'",
+    },
+    BuiltinRule {
+        key: "builtin.missing-required-tags",
+        name: "Resource is missing required tags",
+        severity: Severity::Low,
+        remediation: "Add the missing tags, or have the provisioning pipeline apply them by default.",
+        resource_types: &[],
+        expression: "required_tags.exists(t, !(t in resource.tags))",
+    },
+];
+
+pub fn builtin_rules() -> Vec<ComplianceRule> {
+    BUILTIN_RULES
+        .iter()
+        .map(|rule| ComplianceRule {
+            key: rule.key.to_string(),
+            name: rule.name.to_string(),
+            description: String::new(),
+            severity: rule.severity,
+            remediation: rule.remediation.to_string(),
+            resource_types: rule.resource_types.iter().map(|t| t.to_string()).collect(),
+            expression: rule.expression.to_string(),
+            enabled: true,
+            builtin: true,
+        })
+        .collect()
+}
+
+// One rule's verdict on one resource. Open (or being remediated) until a scan or a remediation
+// rescan no longer sees the violation; if it comes back later a new finding is opened.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Finding {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub rule_key: String,
+    pub severity: Severity,
+    pub provider: String,
+    pub account_id: String,
+    pub resource_key: String,
+    pub region: String,
+    pub resource_type: String,
+    pub resource_name: Option<String>,
+    pub project: Option<String>,
+    pub status: FindingStatus,
+    // The latest remediation run for this finding
+    pub remediation_id: Option<Uuid>,
+    pub first_seen_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    pub resolved_at: Option<DateTime<Utc>>,
+}
+
+type FindingKey = (String, String, String, String, String);
+
+impl Finding {
+    fn key(&self) -> FindingKey {
+        (
+            self.rule_key.clone(),
+            self.provider.clone(),
+            self.account_id.clone(),
+            self.resource_key.clone(),
+            self.region.clone(),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FindingStatus {
+    #[default]
+    Open,
+    Remediating,
+    Resolved,
+}
+
+impl FindingStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FindingStatus::Open => "open",
+            FindingStatus::Remediating => "remediating",
+            FindingStatus::Resolved => "resolved",
+        }
+    }
+
+    pub fn parse(value: &str) -> AppResult<Self> {
+        serde_json::from_value(Value::String(value.to_string()))
+            .map_err(|_| AppError::Validation(format!("Unknown finding status '{}'", value)))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FindingFilter {
+    pub status: FindingStatus,
+    pub rule_key: Option<String>,
+    pub severity: Option<Severity>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ComplianceScore {
+    // Resources at least one enabled rule applied to
+    pub resources: u64,
+    pub compliant: u64,
+    pub score: f64,
+}
+
+impl ComplianceScore {
+    fn add(&mut self, compliant: bool) {
+        self.resources += 1;
+        self.compliant += compliant as u64;
+        self.score = self.compliant as f64 / self.resources as f64;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ComplianceScan {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub started_at: DateTime<Utc>,
+    pub completed_at: DateTime<Utc>,
+    pub rules: u64,
+    // Rule evaluations that errored, e.g. on an attribute the rule doesn't guard with has()
+    pub evaluation_errors: u64,
+    pub opened: u64,
+    pub resolved: u64,
+    pub overall: ComplianceScore,
+    pub providers: BTreeMap<String, ComplianceScore>,
+    // Keyed by the project tag; untagged resources only count towards `overall` and `providers`
+    pub projects: BTreeMap<String, ComplianceScore>,
+    pub open_by_severity: BTreeMap<Severity, u64>,
+}
+
+#[async_trait]
+pub trait ComplianceStore: Send + Sync {
+    async fn custom_rules(&self, tenant_id: Uuid) -> AppResult<Vec<ComplianceRule>>;
+    async fn save_rule(&self, tenant_id: Uuid, rule: &ComplianceRule) -> AppResult<()>;
+    async fn delete_rule(&self, tenant_id: Uuid, key: &str) -> AppResult<bool>;
+    // Only built-in rules the tenant has toggled appear
+    async fn builtin_settings(&self, tenant_id: Uuid) -> AppResult<HashMap<String, bool>>;
+    async fn set_builtin_enabled(&self, tenant_id: Uuid, key: &str, enabled: bool) -> AppResult<()>;
+    // Findings not yet resolved, including those being remediated
+    async fn open_findings(&self, tenant_id: Uuid) -> AppResult<Vec<Finding>>;
+    async fn finding(&self, tenant_id: Uuid, id: Uuid) -> AppResult<Option<Finding>>;
+    async fn update_finding(&self, finding: &Finding) -> AppResult<()>;
+    async fn findings(&self, tenant_id: Uuid, filter: &FindingFilter, limit: i64) -> AppResult<Vec<Finding>>;
+    // Saves the scan with its new and updated findings, resolving `resolved`, all or nothing
+    async fn apply_scan(&self, scan: &ComplianceScan, findings: &[Finding], resolved: &[Uuid]) -> AppResult<()>;
+    async fn latest_scan(&self, tenant_id: Uuid) -> AppResult<Option<ComplianceScan>>;
+}
+
+// Pages through a tenant's current resources
+#[async_trait]
+pub trait ResourceSource: Send + Sync {
+    async fn page(&self, tenant_id: Uuid, after: Option<Uuid>) -> AppResult<InventoryPage>;
+}
+
+#[async_trait]
+impl ResourceSource for InventoryService {
+    async fn page(&self, tenant_id: Uuid, after: Option<Uuid>) -> AppResult<InventoryPage> {
+        self.search(tenant_id, &InventoryQuery::default(), after, MAX_PAGE_SIZE).await
+    }
+}
+
+pub struct ComplianceService {
+    store: Arc<dyn ComplianceStore>,
+    resources: Option<Arc<dyn ResourceSource>>,
+    required_tags: Vec<String>,
+    project_tag: String,
+    // Scans of the same tenant would race on the open findings; they run one at a time
+    scan_lock: tokio::sync::Mutex<()>,
+}
+
+impl ComplianceService {
+    pub fn new(store: Arc<dyn ComplianceStore>) -> Self {
+        Self {
+            store,
+            resources: None,
+            required_tags: DEFAULT_REQUIRED_TAGS.iter().map(|t| t.to_string()).collect(),
+            project_tag: DEFAULT_PROJECT_TAG.to_string(),
+            scan_lock: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    pub fn with_resources(mut self, resources: Arc<dyn ResourceSource>) -> Self {
+        self.resources = Some(resources);
+        self
+    }
+
+    pub fn with_required_tags<S: Into<String>>(mut self, tags: impl IntoIterator<Item = S>) -> Self {
+        self.required_tags = tags.into_iter().map(Into::into).collect();
+        self
+    }
+
+    // The tag that assigns a resource to a project for the per-project scores
+    pub fn with_project_tag(mut self, tag: impl Into<String>) -> Self {
+        self.project_tag = tag.into();
+        self
+    }
+
+    pub async fn rules(&self, tenant_id: Uuid) -> AppResult<Vec<ComplianceRule>> {
+        let settings = self.store.builtin_settings(tenant_id).await?;
+        let mut rules = builtin_rules();
+        for rule in &mut rules {
+            rule.enabled = settings.get(&rule.key).copied().unwrap_or(true);
+        }
+        rules.extend(self.store.custom_rules(tenant_id).await?);
+        Ok(rules)
+    }
+
+    pub async fn rule(&self, tenant_id: Uuid, key: &str) -> AppResult<ComplianceRule> {
+        self.rules(tenant_id)
+            .await?
+            .into_iter()
+            .find(|r| r.key == key)
+            .ok_or_else(|| AppError::NotFound("Compliance rule not found".into()))
+    }
+
+    pub async fn create_rule(&self, tenant_id: Uuid, rule: NewComplianceRule) -> AppResult<ComplianceRule> {
+        let valid_key = rule.key.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+        if rule.key.is_empty() || rule.key.len() > MAX_KEY_LEN || !valid_key {
+            return Err(AppError::Validation(format!(
+                "Rule keys are 1-{} lowercase letters, digits and dashes",
+                MAX_KEY_LEN
+            )));
+        }
+        if rule.name.trim().is_empty() {
+            return Err(AppError::Validation("Rule name is required".into()));
+        }
+        Program::parse(&rule.expression, VARIABLES)?;
+        if self.store.custom_rules(tenant_id).await?.iter().any(|r| r.key == rule.key) {
+            return Err(AppError::Conflict {
+                message: format!("A rule with key '{}' already exists", rule.key),
+                current: None,
+            });
+        }
+        let rule = ComplianceRule {
+            key: rule.key,
+            name: rule.name,
+            description: rule.description,
+            severity: rule.severity,
+            remediation: rule.remediation,
+            resource_types: rule.resource_types,
+            expression: rule.expression,
+            enabled: true,
+            builtin: false,
+        };
+        self.store.save_rule(tenant_id, &rule).await?;
+        Ok(rule)
+    }
+
+    pub async fn set_enabled(&self, tenant_id: Uuid, key: &str, enabled: bool) -> AppResult<ComplianceRule> {
+        let mut rule = self.rule(tenant_id, key).await?;
+        rule.enabled = enabled;
+        if rule.builtin {
+            self.store.set_builtin_enabled(tenant_id, key, enabled).await?;
+        } else {
+            self.store.save_rule(tenant_id, &rule).await?;
+        }
+        Ok(rule)
+    }
+
+    pub async fn delete_rule(&self, tenant_id: Uuid, key: &str) -> AppResult<()> {
+        if key.starts_with(BUILTIN_PREFIX) {
+            return Err(AppError::Validation("Built-in rules can be disabled but not deleted".into()));
+        }
+        if !self.store.delete_rule(tenant_id, key).await? {
+            return Err(AppError::NotFound("Compliance rule not found".into()));
+        }
+        Ok(())
+    }
+
+    pub async fn findings(&self, tenant_id: Uuid, filter: &FindingFilter, limit: i64) -> AppResult<Vec<Finding>> {
+        self.store.findings(tenant_id, filter, limit).await
+    }
+
+    pub async fn finding(&self, tenant_id: Uuid, id: Uuid) -> AppResult<Finding> {
+        self.store
+            .finding(tenant_id, id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Finding not found".into()))
+    }
+
+    // Applies `change` to the stored finding between scans, so a scan can't overwrite it with
+    // what it read before the change
+    pub async fn update_finding(&self, tenant_id: Uuid, id: Uuid, change: impl FnOnce(&mut Finding)) -> AppResult<Finding> {
+        let _guard = self.scan_lock.lock().await;
+        let mut finding = self.finding(tenant_id, id).await?;
+        change(&mut finding);
+        self.store.update_finding(&finding).await?;
+        Ok(finding)
+    }
+
+    pub fn violates(&self, rule: &ComplianceRule, resource: &InventoryResource) -> AppResult<bool> {
+        let program = Program::parse(&rule.expression, VARIABLES)?;
+        let value = serde_json::to_value(resource).map_err(|e| AppError::Internal(e.to_string()))?;
+        Ok(rule.applies_to(resource) && program.evaluate_bool(&[("resource", value), ("required_tags", self.required_tags.clone().into())])?)
+    }
+
+    pub async fn summary(&self, tenant_id: Uuid) -> AppResult<ComplianceScan> {
+        self.store
+            .latest_scan(tenant_id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("No compliance scan has run yet".into()))
+    }
+
+    // Evaluates every enabled rule against the tenant's whole inventory. Open findings the scan
+    // no longer sees, including those of rules since disabled or deleted, are resolved.
+    pub async fn scan(&self, tenant_id: Uuid, now: DateTime<Utc>) -> AppResult<ComplianceScan> {
+        let source = self
+            .resources
+            .as_ref()
+            .ok_or_else(|| AppError::Configuration("No resource source configured for compliance scans".into()))?;
+        let _guard = self.scan_lock.lock().await;
+
+        let mut rules = Vec::new();
+        for rule in self.rules(tenant_id).await?.into_iter().filter(|r| r.enabled) {
+            match Program::parse(&rule.expression, VARIABLES) {
+                Ok(program) => rules.push((rule, program)),
+                Err(e) => warn!("Skipping compliance rule {} for tenant {}: {}", rule.key, tenant_id, e),
+            }
+        }
+        let required_tags = Value::from(self.required_tags.clone());
+        let mut open: HashMap<FindingKey, Finding> =
+            self.store.open_findings(tenant_id).await?.into_iter().map(|f| (f.key(), f)).collect();
+        let mut scan = ComplianceScan {
+            id: Uuid::new_v4(),
+            tenant_id,
+            started_at: now,
+            completed_at: now,
+            rules: rules.len() as u64,
+            evaluation_errors: 0,
+            opened: 0,
+            resolved: 0,
+            overall: ComplianceScore::default(),
+            providers: BTreeMap::new(),
+            projects: BTreeMap::new(),
+            open_by_severity: BTreeMap::new(),
+        };
+        let mut findings = Vec::new();
+
+        let mut after = None;
+        loop {
+            let page = source.page(tenant_id, after).await?;
+            for resource in &page.resources {
+                let applicable: Vec<_> = rules.iter().filter(|(rule, _)| rule.applies_to(resource)).collect();
+                if applicable.is_empty() {
+                    continue;
+                }
+                let value = serde_json::to_value(resource).map_err(|e| AppError::Internal(e.to_string()))?;
+                let variables = [("resource", value), ("required_tags", required_tags.clone())];
+                let project = resource.tags.get(&self.project_tag).cloned();
+                let mut compliant = true;
+                for (rule, program) in applicable {
+                    match program.evaluate_bool(&variables) {
+                        Ok(false) => {}
+                        Ok(true) => {
+                            compliant = false;
+                            let finding = violation(rule, resource, project.clone(), now);
+                            let finding = match open.remove(&finding.key()) {
+                                Some(existing) => Finding {
+                                    id: existing.id,
+                                    status: existing.status,
+                                    remediation_id: existing.remediation_id,
+                                    first_seen_at: existing.first_seen_at,
+                                    ..finding
+                                },
+                                None => {
+                                    scan.opened += 1;
+                                    finding
+                                }
+                            };
+                            *scan.open_by_severity.entry(rule.severity).or_default() += 1;
+                            findings.push(finding);
+                        }
+                        Err(_) => scan.evaluation_errors += 1,
+                    }
+                }
+                scan.overall.add(compliant);
+                scan.providers.entry(resource.provider.clone()).or_default().add(compliant);
+                if let Some(project) = project {
+                    scan.projects.entry(project).or_default().add(compliant);
+                }
+            }
+            match page.next_cursor {
+                Some(cursor) => after = Some(cursor),
+                None => break,
+            }
+        }
+
+        let resolved: Vec<Uuid> = open.into_values().map(|f| f.id).collect();
+        scan.resolved = resolved.len() as u64;
+        scan.completed_at = Utc::now().max(now);
+        self.store.apply_scan(&scan, &findings, &resolved).await?;
+        info!(
+            "Compliance scan {} for tenant {}: {} resources, {} opened, {} resolved, {} evaluation errors",
+            scan.id, tenant_id, scan.overall.resources, scan.opened, scan.resolved, scan.evaluation_errors
+        );
+        Ok(scan)
+    }
+}
+
+fn violation(rule: &ComplianceRule, resource: &InventoryResource, project: Option<String>, now: DateTime<Utc>) -> Finding {
+    Finding {
+        id: Uuid::new_v4(),
+        tenant_id: resource.tenant_id,
+        rule_key: rule.key.clone(),
+        severity: rule.severity,
+        provider: resource.provider.clone(),
+        account_id: resource.account_id.clone(),
+        resource_key: resource.resource_key.clone(),
+        region: resource.region.clone(),
+        resource_type: resource.resource_type.clone(),
+        resource_name: resource.name.clone(),
+        project,
+        status: FindingStatus::Open,
+        remediation_id: None,
+        first_seen_at: now,
+        last_seen_at: now,
+        resolved_at: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discovery::{DiscoveredResource, DiscoveryRun};
+    use crate::inventory::InMemoryInventoryStore;
+    use chrono::{Duration, TimeZone};
+    use serde_json::json;
+
+    fn tagged(resource: DiscoveredResource, project: &str) -> DiscoveredResource {
+        resource.with_tags([
+            ("owner".to_string(), "platform".to_string()),
+            ("environment".to_string(), "prod".to_string()),
+            ("project".to_string(), project.to_string()),
+        ])
+    }
+
+    fn fixtures() -> Vec<DiscoveredResource> {
+        let open_ssh = json!([{"cidr": "0.0.0.0/0", "from_port": "0", "to_port": "65535"}]);
+        let office_ssh = json!([{"cidr": "203.0.113.0/24", "from_port": "22", "to_port": "22"}]);
+        vec![
+            tagged(DiscoveredResource::new("s3:bucket", "assets", None, "us-east-1").with_attribute("acl", "public-read"), "web"),
+            tagged(DiscoveredResource::new("s3:bucket", "logs", None, "us-east-1").with_attribute("acl", "private"), "web"),
+            tagged(DiscoveredResource::new("ec2:security-group", "sg-open", None, "us-east-1").with_attribute("ingress", open_ssh), "web"),
+            tagged(DiscoveredResource::new("ec2:security-group", "sg-office", None, "us-east-1").with_attribute("ingress", office_ssh), "data"),
+            tagged(DiscoveredResource::new("ec2:volume", "vol-plain", None, "us-east-1").with_attribute("encrypted", "false"), "data"),
+            tagged(DiscoveredResource::new("ec2:volume", "vol-kms", None, "us-east-1").with_attribute("encrypted", "true"), "data"),
+            DiscoveredResource::new("ec2:instance", "i-untagged", None, "us-east-1").with_tags([("owner".to_string(), "ml".to_string())]),
+        ]
+    }
+
+    async fn setup() -> (Arc<InventoryService>, ComplianceService, Uuid) {
+        let inventory = Arc::new(InventoryService::new(Arc::new(InMemoryInventoryStore::new())));
+        let compliance = ComplianceService::new(Arc::new(InMemoryComplianceStore::new())).with_resources(inventory.clone());
+        (inventory, compliance, Uuid::new_v4())
+    }
+
+    async fn discover(inventory: &InventoryService, tenant_id: Uuid, resources: &[DiscoveredResource], at: DateTime<Utc>) {
+        let run = DiscoveryRun {
+            id: Uuid::new_v4(),
+            tenant_id,
+            provider: "aws".into(),
+            account_id: "123456789012".into(),
+            resource_count: resources.len() as i64,
+            started_at: at,
+            completed_at: at,
+        };
+        inventory.ingest(&run, resources).await.unwrap();
+    }
+
+    fn open_keys(findings: &[Finding]) -> Vec<(String, String)> {
+        let mut keys: Vec<_> = findings.iter().map(|f| (f.rule_key.clone(), f.resource_key.clone())).collect();
+        keys.sort();
+        keys
+    }
+
+    #[tokio::test]
+    async fn test_builtin_rules_on_fixture_resources() {
+        let (inventory, compliance, tenant) = setup().await;
+        let at = Utc.with_ymd_and_hms(2025, 7, 1, 0, 0, 0).unwrap();
+        discover(&inventory, tenant, &fixtures(), at).await;
+
+        let scan = compliance.scan(tenant, at).await.unwrap();
+        let findings = compliance.findings(tenant, &FindingFilter::default(), 100).await.unwrap();
+        assert_eq!(open_keys(&findings), vec![
+            ("builtin.missing-required-tags".to_string(), "ec2:instance/i-untagged".to_string()),
+            ("builtin.open-ssh".to_string(), "ec2:security-group/sg-open".to_string()),
+            ("builtin.public-s3-bucket".to_string(), "s3:bucket/assets".to_string()),
+            ("builtin.unencrypted-volume".to_string(), "ec2:volume/vol-plain".to_string()),
+        ]);
+        assert_eq!((scan.overall.resources, scan.overall.compliant, scan.evaluation_errors), (7, 3, 0));
+        assert_eq!(scan.projects["web"].compliant, 1);
+        assert_eq!(scan.projects["data"].compliant, 2);
+        assert_eq!(scan.open_by_severity[&Severity::High], 2);
+
+        // Toggled off, its finding resolves on the next scan
+        let rule = compliance.set_enabled(tenant, "builtin.missing-required-tags", false).await.unwrap();
+        assert!(!rule.enabled && rule.builtin);
+        let scan = compliance.scan(tenant, at + Duration::hours(1)).await.unwrap();
+        assert_eq!((scan.rules, scan.resolved, scan.overall.resources), (3, 1, 6));
+        assert!(compliance.delete_rule(tenant, "builtin.open-ssh").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_finding_lifecycle_across_scans() {
+        let (inventory, compliance, tenant) = setup().await;
+        let monday = Utc.with_ymd_and_hms(2025, 7, 7, 9, 0, 0).unwrap();
+        let rule = NewComplianceRule {
+            key: "large-volume".into(),
+            name: "Volume over 500 GB".into(),
+            description: String::new(),
+            severity: Severity::Medium,
+            remediation: "Right-size the volume".into(),
+            resource_types: vec!["ec2:volume".into()],
+            expression: "has(resource.attributes.size_gb) && int(resource.attributes.size_gb) > 500".into(),
+        };
+        compliance.create_rule(tenant, rule).await.unwrap();
+        let mut resources = fixtures();
+        resources[4] = resources[4].clone().with_attribute("size_gb", "1000");
+        discover(&inventory, tenant, &resources, monday).await;
+        compliance.scan(tenant, monday).await.unwrap();
+        let first = compliance.findings(tenant, &FindingFilter::default(), 100).await.unwrap();
+        assert_eq!(first.len(), 5);
+
+        // The volume is encrypted and shrunk and the bucket made private; the group stays open
+        let tuesday = monday + Duration::days(1);
+        resources[0] = resources[0].clone().with_attribute("acl", "private");
+        resources[4] = resources[4].clone().with_attribute("encrypted", "true").with_attribute("size_gb", "100");
+        discover(&inventory, tenant, &resources, tuesday).await;
+        let scan = compliance.scan(tenant, tuesday).await.unwrap();
+        assert_eq!((scan.opened, scan.resolved), (0, 3));
+
+        let open = compliance.findings(tenant, &FindingFilter::default(), 100).await.unwrap();
+        assert_eq!(open.len(), 2);
+        let ssh = open.iter().find(|f| f.rule_key == "builtin.open-ssh").unwrap();
+        let before = first.iter().find(|f| f.rule_key == "builtin.open-ssh").unwrap();
+        assert_eq!((ssh.id, ssh.first_seen_at, ssh.last_seen_at), (before.id, monday, tuesday));
+        let resolved = FindingFilter { status: FindingStatus::Resolved, ..Default::default() };
+        let resolved = compliance.findings(tenant, &resolved, 100).await.unwrap();
+        assert_eq!(open_keys(&resolved), vec![
+            ("builtin.public-s3-bucket".to_string(), "s3:bucket/assets".to_string()),
+            ("builtin.unencrypted-volume".to_string(), "ec2:volume/vol-plain".to_string()),
+            ("large-volume".to_string(), "ec2:volume/vol-plain".to_string()),
+        ]);
+        assert!(resolved.iter().all(|f| f.resolved_at == Some(tuesday) && f.last_seen_at == monday));
+        assert_eq!(compliance.summary(tenant).await.unwrap().id, scan.id);
+    }
+
+    #[tokio::test]
+    async fn test_malformed_rule_rejected_at_save() {
+        let (_, compliance, tenant) = setup().await;
+        let rule = |key: &str, expression: &str| NewComplianceRule {
+            key: key.into(),
+            name: "Custom".into(),
+            description: String::new(),
+            severity: Severity::Low,
+            remediation: String::new(),
+            resource_types: Vec::new(),
+            expression: expression.into(),
+        };
+        for expression in ["resource.tags.env ==", "resource.name.lower() == 'x'", "tags.env == 'prod'"] {
+            let error = compliance.create_rule(tenant, rule("bad", expression)).await.unwrap_err();
+            assert!(matches!(error, AppError::Validation(_)), "{}", expression);
+        }
+        assert!(matches!(compliance.create_rule(tenant, rule("Bad Key", "true")).await, Err(AppError::Validation(_))));
+        compliance.create_rule(tenant, rule("ok", "size(resource.tags) == 0")).await.unwrap();
+        assert!(matches!(compliance.create_rule(tenant, rule("ok", "true")).await, Err(AppError::Conflict { .. })));
+        assert_eq!(compliance.rules(tenant).await.unwrap().len(), BUILTIN_RULES.len() + 1);
+    }
+}
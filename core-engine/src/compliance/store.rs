@@ -0,0 +1,459 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::types::Json;
+use sqlx::{PgPool, QueryBuilder}; // CockroachDB uses PostgreSQL protocol
+use uuid::Uuid;
+
+use super::{ComplianceRule, ComplianceScan, ComplianceScore, ComplianceStore, Finding, FindingFilter, FindingStatus, Severity};
+use crate::error::{AppError, AppResult};
+
+// Rows per upsert when saving a scan's findings
+const UPSERT_BATCH: usize = 500;
+
+const FINDING_COLUMNS: &str = "id, tenant_id, rule_key, severity, provider, account_id, resource_key, region, \
+    resource_type, resource_name, project, status, remediation_id, first_seen_at, last_seen_at, resolved_at";
+
+#[derive(sqlx::FromRow)]
+struct RuleRow {
+    key: String,
+    name: String,
+    description: String,
+    severity: String,
+    remediation: String,
+    resource_types: Json<Vec<String>>,
+    expression: String,
+    enabled: bool,
+}
+
+impl TryFrom<RuleRow> for ComplianceRule {
+    type Error = AppError;
+
+    fn try_from(row: RuleRow) -> AppResult<Self> {
+        Ok(Self {
+            key: row.key,
+            name: row.name,
+            description: row.description,
+            severity: Severity::parse(&row.severity)?,
+            remediation: row.remediation,
+            resource_types: row.resource_types.0,
+            expression: row.expression,
+            enabled: row.enabled,
+            builtin: false,
+        })
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct FindingRow {
+    id: Uuid,
+    tenant_id: Uuid,
+    rule_key: String,
+    severity: String,
+    provider: String,
+    account_id: String,
+    resource_key: String,
+    region: String,
+    resource_type: String,
+    resource_name: Option<String>,
+    project: Option<String>,
+    status: String,
+    remediation_id: Option<Uuid>,
+    first_seen_at: DateTime<Utc>,
+    last_seen_at: DateTime<Utc>,
+    resolved_at: Option<DateTime<Utc>>,
+}
+
+impl TryFrom<FindingRow> for Finding {
+    type Error = AppError;
+
+    fn try_from(row: FindingRow) -> AppResult<Self> {
+        Ok(Self {
+            id: row.id,
+            tenant_id: row.tenant_id,
+            rule_key: row.rule_key,
+            severity: Severity::parse(&row.severity)?,
+            provider: row.provider,
+            account_id: row.account_id,
+            resource_key: row.resource_key,
+            region: row.region,
+            resource_type: row.resource_type,
+            resource_name: row.resource_name,
+            project: row.project,
+            status: FindingStatus::parse(&row.status)?,
+            remediation_id: row.remediation_id,
+            first_seen_at: row.first_seen_at,
+            last_seen_at: row.last_seen_at,
+            resolved_at: row.resolved_at,
+        })
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct ScanRow {
+    id: Uuid,
+    tenant_id: Uuid,
+    started_at: DateTime<Utc>,
+    completed_at: DateTime<Utc>,
+    rules: i64,
+    evaluation_errors: i64,
+    opened: i64,
+    resolved: i64,
+    overall: Json<ComplianceScore>,
+    providers: Json<BTreeMap<String, ComplianceScore>>,
+    projects: Json<BTreeMap<String, ComplianceScore>>,
+    open_by_severity: Json<BTreeMap<Severity, u64>>,
+}
+
+impl From<ScanRow> for ComplianceScan {
+    fn from(row: ScanRow) -> Self {
+        Self {
+            id: row.id,
+            tenant_id: row.tenant_id,
+            started_at: row.started_at,
+            completed_at: row.completed_at,
+            rules: row.rules as u64,
+            evaluation_errors: row.evaluation_errors as u64,
+            opened: row.opened as u64,
+            resolved: row.resolved as u64,
+            overall: row.overall.0,
+            providers: row.providers.0,
+            projects: row.projects.0,
+            open_by_severity: row.open_by_severity.0,
+        }
+    }
+}
+
+fn push_finding(mut row: sqlx::query_builder::Separated<'_, '_, sqlx::Postgres, &'static str>, finding: &Finding) {
+    row.push_bind(finding.id)
+        .push_bind(finding.tenant_id)
+        .push_bind(finding.rule_key.clone())
+        .push_bind(finding.severity.as_str())
+        .push_bind(finding.provider.clone())
+        .push_bind(finding.account_id.clone())
+        .push_bind(finding.resource_key.clone())
+        .push_bind(finding.region.clone())
+        .push_bind(finding.resource_type.clone())
+        .push_bind(finding.resource_name.clone())
+        .push_bind(finding.project.clone())
+        .push_bind(finding.status.as_str())
+        .push_bind(finding.remediation_id)
+        .push_bind(finding.first_seen_at)
+        .push_bind(finding.last_seen_at)
+        .push_bind(finding.resolved_at);
+}
+
+fn rows_to<R, T: TryFrom<R, Error = AppError>>(rows: Vec<R>) -> AppResult<Vec<T>> {
+    rows.into_iter().map(T::try_from).collect()
+}
+
+pub struct PgComplianceStore {
+    pool: PgPool,
+}
+
+impl PgComplianceStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl ComplianceStore for PgComplianceStore {
+    async fn custom_rules(&self, tenant_id: Uuid) -> AppResult<Vec<ComplianceRule>> {
+        let rows = sqlx::query_as::<_, RuleRow>(
+            r#"SELECT key, name, description, severity, remediation, resource_types, expression, enabled
+            FROM compliance_rules WHERE tenant_id = $1 ORDER BY key"#
+        )
+        .bind(tenant_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        rows_to(rows)
+    }
+
+    async fn save_rule(&self, tenant_id: Uuid, rule: &ComplianceRule) -> AppResult<()> {
+        sqlx::query(
+            r#"UPSERT INTO compliance_rules
+            (tenant_id, key, name, description, severity, remediation, resource_types, expression, enabled)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"#
+        )
+        .bind(tenant_id)
+        .bind(&rule.key)
+        .bind(&rule.name)
+        .bind(&rule.description)
+        .bind(rule.severity.as_str())
+        .bind(&rule.remediation)
+        .bind(Json(&rule.resource_types))
+        .bind(&rule.expression)
+        .bind(rule.enabled)
+        .execute(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(())
+    }
+
+    async fn delete_rule(&self, tenant_id: Uuid, key: &str) -> AppResult<bool> {
+        let result = sqlx::query("DELETE FROM compliance_rules WHERE tenant_id = $1 AND key = $2")
+            .bind(tenant_id)
+            .bind(key)
+            .execute(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn builtin_settings(&self, tenant_id: Uuid) -> AppResult<HashMap<String, bool>> {
+        let rows: Vec<(String, bool)> =
+            sqlx::query_as("SELECT rule_key, enabled FROM compliance_builtin_settings WHERE tenant_id = $1")
+                .bind(tenant_id)
+                .fetch_all(&self.pool)
+                .await
+                .map_err(AppError::Database)?;
+
+        Ok(rows.into_iter().collect())
+    }
+
+    async fn set_builtin_enabled(&self, tenant_id: Uuid, key: &str, enabled: bool) -> AppResult<()> {
+        sqlx::query("UPSERT INTO compliance_builtin_settings (tenant_id, rule_key, enabled) VALUES ($1, $2, $3)")
+            .bind(tenant_id)
+            .bind(key)
+            .bind(enabled)
+            .execute(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(())
+    }
+
+    async fn open_findings(&self, tenant_id: Uuid) -> AppResult<Vec<Finding>> {
+        let rows = sqlx::query_as::<_, FindingRow>(&format!(
+            "SELECT {} FROM compliance_findings WHERE tenant_id = $1 AND resolved_at IS NULL",
+            FINDING_COLUMNS
+        ))
+        .bind(tenant_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        rows_to(rows)
+    }
+
+    async fn finding(&self, tenant_id: Uuid, id: Uuid) -> AppResult<Option<Finding>> {
+        let row = sqlx::query_as::<_, FindingRow>(&format!(
+            "SELECT {} FROM compliance_findings WHERE tenant_id = $1 AND id = $2",
+            FINDING_COLUMNS
+        ))
+        .bind(tenant_id)
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        row.map(Finding::try_from).transpose()
+    }
+
+    async fn update_finding(&self, finding: &Finding) -> AppResult<()> {
+        let mut upsert = QueryBuilder::new(format!("UPSERT INTO compliance_findings ({}) ", FINDING_COLUMNS));
+        upsert.push_values(std::iter::once(finding), push_finding);
+        upsert.build().execute(&self.pool).await.map_err(AppError::Database)?;
+
+        Ok(())
+    }
+
+    async fn findings(&self, tenant_id: Uuid, filter: &FindingFilter, limit: i64) -> AppResult<Vec<Finding>> {
+        let mut query = QueryBuilder::new(format!("SELECT {} FROM compliance_findings WHERE tenant_id = ", FINDING_COLUMNS));
+        query.push_bind(tenant_id);
+        query.push(" AND status = ").push_bind(filter.status.as_str());
+        if let Some(rule_key) = &filter.rule_key {
+            query.push(" AND rule_key = ").push_bind(rule_key);
+        }
+        if let Some(severity) = filter.severity {
+            query.push(" AND severity = ").push_bind(severity.as_str());
+        }
+        query.push(" ORDER BY last_seen_at DESC, id LIMIT ").push_bind(limit);
+        let rows = query
+            .build_query_as::<FindingRow>()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        rows_to(rows)
+    }
+
+    async fn apply_scan(&self, scan: &ComplianceScan, findings: &[Finding], resolved: &[Uuid]) -> AppResult<()> {
+        let mut tx = self.pool.begin().await.map_err(AppError::Database)?;
+        for batch in findings.chunks(UPSERT_BATCH) {
+            let mut upsert = QueryBuilder::new(format!("UPSERT INTO compliance_findings ({}) ", FINDING_COLUMNS));
+            upsert.push_values(batch, push_finding);
+            upsert.build().execute(&mut *tx).await.map_err(AppError::Database)?;
+        }
+        if !resolved.is_empty() {
+            sqlx::query(
+                "UPDATE compliance_findings SET status = 'resolved', resolved_at = $1 WHERE tenant_id = $2 AND id = ANY($3)",
+            )
+                .bind(scan.started_at)
+                .bind(scan.tenant_id)
+                .bind(resolved)
+                .execute(&mut *tx)
+                .await
+                .map_err(AppError::Database)?;
+        }
+        sqlx::query(
+            r#"INSERT INTO compliance_scans
+            (id, tenant_id, started_at, completed_at, rules, evaluation_errors, opened, resolved,
+             overall, providers, projects, open_by_severity)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)"#
+        )
+        .bind(scan.id)
+        .bind(scan.tenant_id)
+        .bind(scan.started_at)
+        .bind(scan.completed_at)
+        .bind(scan.rules as i64)
+        .bind(scan.evaluation_errors as i64)
+        .bind(scan.opened as i64)
+        .bind(scan.resolved as i64)
+        .bind(Json(&scan.overall))
+        .bind(Json(&scan.providers))
+        .bind(Json(&scan.projects))
+        .bind(Json(&scan.open_by_severity))
+        .execute(&mut *tx)
+        .await
+        .map_err(AppError::Database)?;
+        tx.commit().await.map_err(AppError::Database)?;
+
+        Ok(())
+    }
+
+    async fn latest_scan(&self, tenant_id: Uuid) -> AppResult<Option<ComplianceScan>> {
+        let row = sqlx::query_as::<_, ScanRow>(
+            r#"SELECT id, tenant_id, started_at, completed_at, rules, evaluation_errors, opened, resolved,
+                overall, providers, projects, open_by_severity
+            FROM compliance_scans WHERE tenant_id = $1
+            ORDER BY started_at DESC LIMIT 1"#
+        )
+        .bind(tenant_id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(row.map(ComplianceScan::from))
+    }
+}
+
+#[derive(Default)]
+struct ComplianceState {
+    rules: Vec<(Uuid, ComplianceRule)>,
+    settings: HashMap<(Uuid, String), bool>,
+    findings: Vec<Finding>,
+    scans: Vec<ComplianceScan>,
+}
+
+#[derive(Default)]
+pub struct InMemoryComplianceStore {
+    state: Mutex<ComplianceState>,
+}
+
+impl InMemoryComplianceStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl ComplianceStore for InMemoryComplianceStore {
+    async fn custom_rules(&self, tenant_id: Uuid) -> AppResult<Vec<ComplianceRule>> {
+        let state = self.state.lock().unwrap();
+        let mut rules: Vec<ComplianceRule> =
+            state.rules.iter().filter(|(t, _)| *t == tenant_id).map(|(_, r)| r.clone()).collect();
+        rules.sort_by(|a, b| a.key.cmp(&b.key));
+        Ok(rules)
+    }
+
+    async fn save_rule(&self, tenant_id: Uuid, rule: &ComplianceRule) -> AppResult<()> {
+        let mut state = self.state.lock().unwrap();
+        state.rules.retain(|(t, r)| !(*t == tenant_id && r.key == rule.key));
+        state.rules.push((tenant_id, rule.clone()));
+        Ok(())
+    }
+
+    async fn delete_rule(&self, tenant_id: Uuid, key: &str) -> AppResult<bool> {
+        let mut state = self.state.lock().unwrap();
+        let before = state.rules.len();
+        state.rules.retain(|(t, r)| !(*t == tenant_id && r.key == key));
+        Ok(state.rules.len() < before)
+    }
+
+    async fn builtin_settings(&self, tenant_id: Uuid) -> AppResult<HashMap<String, bool>> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .settings
+            .iter()
+            .filter(|((t, _), _)| *t == tenant_id)
+            .map(|((_, key), enabled)| (key.clone(), *enabled))
+            .collect())
+    }
+
+    async fn set_builtin_enabled(&self, tenant_id: Uuid, key: &str, enabled: bool) -> AppResult<()> {
+        self.state.lock().unwrap().settings.insert((tenant_id, key.to_string()), enabled);
+        Ok(())
+    }
+
+    async fn open_findings(&self, tenant_id: Uuid) -> AppResult<Vec<Finding>> {
+        let state = self.state.lock().unwrap();
+        Ok(state.findings.iter().filter(|f| f.tenant_id == tenant_id && f.resolved_at.is_none()).cloned().collect())
+    }
+
+    async fn finding(&self, tenant_id: Uuid, id: Uuid) -> AppResult<Option<Finding>> {
+        let state = self.state.lock().unwrap();
+        Ok(state.findings.iter().find(|f| f.tenant_id == tenant_id && f.id == id).cloned())
+    }
+
+    async fn update_finding(&self, finding: &Finding) -> AppResult<()> {
+        let mut state = self.state.lock().unwrap();
+        state.findings.retain(|f| f.id != finding.id);
+        state.findings.push(finding.clone());
+        Ok(())
+    }
+
+    async fn findings(&self, tenant_id: Uuid, filter: &FindingFilter, limit: i64) -> AppResult<Vec<Finding>> {
+        let state = self.state.lock().unwrap();
+        let mut findings: Vec<Finding> = state
+            .findings
+            .iter()
+            .filter(|f| {
+                f.tenant_id == tenant_id
+                    && f.status == filter.status
+                    && filter.rule_key.as_ref().is_none_or(|k| *k == f.rule_key)
+                    && filter.severity.is_none_or(|s| s == f.severity)
+            })
+            .cloned()
+            .collect();
+        findings.sort_by(|a, b| b.last_seen_at.cmp(&a.last_seen_at).then_with(|| a.id.cmp(&b.id)));
+        findings.truncate(limit.max(0) as usize);
+        Ok(findings)
+    }
+
+    async fn apply_scan(&self, scan: &ComplianceScan, findings: &[Finding], resolved: &[Uuid]) -> AppResult<()> {
+        let mut state = self.state.lock().unwrap();
+        for finding in findings {
+            state.findings.retain(|f| f.id != finding.id);
+            state.findings.push(finding.clone());
+        }
+        for finding in state.findings.iter_mut().filter(|f| resolved.contains(&f.id)) {
+            finding.status = FindingStatus::Resolved;
+            finding.resolved_at = Some(scan.started_at);
+        }
+        state.scans.push(scan.clone());
+        Ok(())
+    }
+
+    async fn latest_scan(&self, tenant_id: Uuid) -> AppResult<Option<ComplianceScan>> {
+        let state = self.state.lock().unwrap();
+        Ok(state.scans.iter().filter(|s| s.tenant_id == tenant_id).max_by_key(|s| s.started_at).cloned())
+    }
+}
@@ -0,0 +1,377 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use axum::{
+    async_trait,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use tracing::info;
+use uuid::Uuid;
+
+use crate::error::{AppError, AppResult};
+
+pub mod store;
+
+pub use store::{InMemoryDeviceStore, PgDeviceStore};
+
+// RFC 8628 device authorization grant, for the CLI on machines without a browser
+pub const DEVICE_CODE_GRANT: &str = "urn:ietf:params:oauth:grant-type:device_code";
+pub const DEFAULT_CODE_TTL_SECS: i64 = 600;
+pub const DEFAULT_INTERVAL_SECS: i64 = 5;
+// Added to the interval each time a client polls too fast (RFC 8628 section 3.5)
+pub const SLOW_DOWN_STEP_SECS: i64 = 5;
+const MAX_CODES_PER_CLIENT: usize = 10;
+const MAX_VERIFY_FAILURES: usize = 5;
+const LIMIT_WINDOW_SECS: i64 = 600;
+// No vowels or look-alikes, so codes don't spell words and survive being read aloud
+const USER_CODE_ALPHABET: &[u8] = b"BCDFGHJKLMNPQRSTVWXZ";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "VARCHAR", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum DeviceStatus {
+    Pending,
+    Approved,
+    Denied,
+    // A token has been issued; the device code is spent
+    Consumed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct DeviceAuthorization {
+    pub id: Uuid,
+    #[serde(skip_serializing)]
+    pub device_code_hash: String,
+    pub user_code: String,
+    pub client_id: String,
+    pub status: DeviceStatus,
+    pub user_id: Option<Uuid>,
+    pub interval_secs: i64,
+    pub last_polled_at: Option<DateTime<Utc>>,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+// Body of `POST /auth/device`
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceCodeResponse {
+    pub device_code: String,
+    pub user_code: String,
+    pub verification_uri: String,
+    pub verification_uri_complete: String,
+    pub expires_in: i64,
+    pub interval: i64,
+}
+
+// Token endpoint errors use the OAuth error codes rather than the API's usual shape
+#[derive(Debug)]
+pub enum DeviceGrantError {
+    AuthorizationPending,
+    SlowDown { interval: i64 },
+    AccessDenied,
+    ExpiredToken,
+    InvalidGrant,
+    UnsupportedGrantType,
+    Other(AppError),
+}
+
+impl DeviceGrantError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            DeviceGrantError::AuthorizationPending => "authorization_pending",
+            DeviceGrantError::SlowDown { .. } => "slow_down",
+            DeviceGrantError::AccessDenied => "access_denied",
+            DeviceGrantError::ExpiredToken => "expired_token",
+            DeviceGrantError::InvalidGrant => "invalid_grant",
+            DeviceGrantError::UnsupportedGrantType => "unsupported_grant_type",
+            DeviceGrantError::Other(_) => "server_error",
+        }
+    }
+}
+
+impl From<AppError> for DeviceGrantError {
+    fn from(error: AppError) -> Self {
+        DeviceGrantError::Other(error)
+    }
+}
+
+impl IntoResponse for DeviceGrantError {
+    fn into_response(self) -> Response {
+        let description = match &self {
+            DeviceGrantError::AuthorizationPending => "The user has not approved this device yet".to_string(),
+            DeviceGrantError::SlowDown { interval } => format!("Polling too fast; wait {} seconds between requests", interval),
+            DeviceGrantError::AccessDenied => "The user denied this device".to_string(),
+            DeviceGrantError::ExpiredToken => "The device code has expired; start again".to_string(),
+            DeviceGrantError::InvalidGrant => "Unknown or already used device code".to_string(),
+            DeviceGrantError::UnsupportedGrantType => format!("Only {} is supported here", DEVICE_CODE_GRANT),
+            DeviceGrantError::Other(_) => String::new(),
+        };
+        if let DeviceGrantError::Other(error) = self {
+            return error.into_response();
+        }
+        let mut body = json!({ "error": self.code(), "error_description": description });
+        if let DeviceGrantError::SlowDown { interval } = self {
+            body["interval"] = json!(interval);
+        }
+        (StatusCode::BAD_REQUEST, Json(body)).into_response()
+    }
+}
+
+#[async_trait]
+pub trait DeviceStore: Send + Sync {
+    async fn insert(&self, authorization: &DeviceAuthorization) -> AppResult<()>;
+    async fn find_by_device_code(&self, device_code_hash: &str) -> AppResult<Option<DeviceAuthorization>>;
+    async fn find_pending_by_user_code(&self, user_code: &str, now: DateTime<Utc>) -> AppResult<Option<DeviceAuthorization>>;
+    // Both only move a pending authorization
+    async fn approve(&self, id: Uuid, user_id: Uuid) -> AppResult<bool>;
+    async fn deny(&self, id: Uuid, user_id: Uuid) -> AppResult<bool>;
+    async fn record_poll(&self, id: Uuid, at: DateTime<Utc>, interval_secs: i64) -> AppResult<()>;
+    // Approved to consumed, exactly once
+    async fn consume(&self, id: Uuid) -> AppResult<bool>;
+}
+
+fn hash_code(raw: &str) -> String {
+    format!("{:x}", Sha256::digest(raw.as_bytes()))
+}
+
+fn generate_user_code() -> String {
+    let bytes = *Uuid::new_v4().as_bytes();
+    let chars: String = bytes[..8].iter().map(|b| USER_CODE_ALPHABET[*b as usize % USER_CODE_ALPHABET.len()] as char).collect();
+    format!("{}-{}", &chars[..4], &chars[4..])
+}
+
+// Users type codes however they like: lower case, without the dash, with spaces
+pub fn normalize_user_code(input: &str) -> String {
+    let chars: String = input.chars().filter(|c| c.is_ascii_alphanumeric()).map(|c| c.to_ascii_uppercase()).collect();
+    if chars.len() == 8 {
+        format!("{}-{}", &chars[..4], &chars[4..])
+    } else {
+        chars
+    }
+}
+
+// Sliding window of attempts per key; counts are per replica, which is enough to stop a
+// single client hammering the endpoints
+#[derive(Default)]
+struct AttemptLimiter {
+    attempts: Mutex<HashMap<String, VecDeque<DateTime<Utc>>>>,
+}
+
+impl AttemptLimiter {
+    fn check(&self, key: &str, max: usize, now: DateTime<Utc>) -> AppResult<()> {
+        let mut attempts = self.attempts.lock().unwrap();
+        let window = attempts.entry(key.to_string()).or_default();
+        while window.front().is_some_and(|at| now - *at >= Duration::seconds(LIMIT_WINDOW_SECS)) {
+            window.pop_front();
+        }
+        if window.len() >= max {
+            let retry_after = window.front().map_or(LIMIT_WINDOW_SECS, |at| LIMIT_WINDOW_SECS - (now - *at).num_seconds());
+            return Err(AppError::RateLimited {
+                message: "Too many device authorization attempts".into(),
+                retry_after_secs: retry_after.max(1) as u64,
+            });
+        }
+        Ok(())
+    }
+
+    fn record(&self, key: &str, now: DateTime<Utc>) {
+        self.attempts.lock().unwrap().entry(key.to_string()).or_default().push_back(now);
+    }
+}
+
+pub struct DeviceAuthService {
+    store: Arc<dyn DeviceStore>,
+    verification_uri: String,
+    code_ttl: Duration,
+    interval: i64,
+    issued: AttemptLimiter,
+    failed_verifications: AttemptLimiter,
+}
+
+impl DeviceAuthService {
+    pub fn new(store: Arc<dyn DeviceStore>) -> Self {
+        Self {
+            store,
+            verification_uri: "/device".to_string(),
+            code_ttl: Duration::seconds(DEFAULT_CODE_TTL_SECS),
+            interval: DEFAULT_INTERVAL_SECS,
+            issued: AttemptLimiter::default(),
+            failed_verifications: AttemptLimiter::default(),
+        }
+    }
+
+    // Page where a signed-in user enters the code, shown to the CLI user
+    pub fn with_verification_uri(mut self, uri: impl Into<String>) -> Self {
+        self.verification_uri = uri.into();
+        self
+    }
+
+    pub fn with_code_ttl(mut self, ttl: Duration) -> Self {
+        self.code_ttl = ttl;
+        self
+    }
+
+    pub fn with_interval(mut self, interval_secs: i64) -> Self {
+        self.interval = interval_secs;
+        self
+    }
+
+    pub async fn start(&self, client_id: &str, now: DateTime<Utc>) -> AppResult<DeviceCodeResponse> {
+        let client_id = client_id.trim();
+        if client_id.is_empty() {
+            return Err(AppError::Validation("client_id is required".into()));
+        }
+        self.issued.check(client_id, MAX_CODES_PER_CLIENT, now)?;
+
+        let device_code = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+        let authorization = DeviceAuthorization {
+            id: Uuid::new_v4(),
+            device_code_hash: hash_code(&device_code),
+            user_code: generate_user_code(),
+            client_id: client_id.to_string(),
+            status: DeviceStatus::Pending,
+            user_id: None,
+            interval_secs: self.interval,
+            last_polled_at: None,
+            expires_at: now + self.code_ttl,
+            created_at: now,
+        };
+        self.store.insert(&authorization).await?;
+        self.issued.record(client_id, now);
+        info!("Issued device code {} for client {}", authorization.id, client_id);
+
+        Ok(DeviceCodeResponse {
+            device_code,
+            verification_uri_complete: format!("{}?user_code={}", self.verification_uri, authorization.user_code),
+            user_code: authorization.user_code,
+            verification_uri: self.verification_uri.clone(),
+            expires_in: self.code_ttl.num_seconds(),
+            interval: self.interval,
+        })
+    }
+
+    // A signed-in user approving (or turning down) the code shown on their terminal
+    pub async fn verify(&self, user_id: Uuid, user_code: &str, approve: bool, now: DateTime<Utc>) -> AppResult<DeviceAuthorization> {
+        let key = user_id.to_string();
+        self.failed_verifications.check(&key, MAX_VERIFY_FAILURES, now)?;
+        let Some(mut authorization) = self.store.find_pending_by_user_code(&normalize_user_code(user_code), now).await? else {
+            self.failed_verifications.record(&key, now);
+            return Err(AppError::NotFound("Unknown or expired code".into()));
+        };
+        let moved = if approve {
+            self.store.approve(authorization.id, user_id).await?
+        } else {
+            self.store.deny(authorization.id, user_id).await?
+        };
+        if !moved {
+            return Err(AppError::Conflict { message: "This code has already been used".into(), current: None });
+        }
+        authorization.status = if approve { DeviceStatus::Approved } else { DeviceStatus::Denied };
+        authorization.user_id = Some(user_id);
+        info!("Device code {} {} by {}", authorization.id, if approve { "approved" } else { "denied" }, user_id);
+        Ok(authorization)
+    }
+
+    // One poll from the device; the approving user comes back exactly once
+    pub async fn poll(&self, device_code: &str, client_id: &str, now: DateTime<Utc>) -> Result<Uuid, DeviceGrantError> {
+        let authorization = self
+            .store
+            .find_by_device_code(&hash_code(device_code.trim()))
+            .await?
+            .filter(|a| a.client_id == client_id.trim())
+            .ok_or(DeviceGrantError::InvalidGrant)?;
+        if authorization.status == DeviceStatus::Consumed {
+            return Err(DeviceGrantError::InvalidGrant);
+        }
+        if authorization.expires_at <= now {
+            return Err(DeviceGrantError::ExpiredToken);
+        }
+
+        let too_fast = authorization
+            .last_polled_at
+            .is_some_and(|at| now - at < Duration::seconds(authorization.interval_secs));
+        let interval = if too_fast { authorization.interval_secs + SLOW_DOWN_STEP_SECS } else { authorization.interval_secs };
+        self.store.record_poll(authorization.id, now, interval).await?;
+        if too_fast {
+            return Err(DeviceGrantError::SlowDown { interval });
+        }
+
+        match (authorization.status, authorization.user_id) {
+            (DeviceStatus::Pending, _) => Err(DeviceGrantError::AuthorizationPending),
+            (DeviceStatus::Denied, _) => Err(DeviceGrantError::AccessDenied),
+            (DeviceStatus::Approved, Some(user_id)) => {
+                if !self.store.consume(authorization.id).await? {
+                    return Err(DeviceGrantError::InvalidGrant);
+                }
+                info!("Device code {} exchanged for a token for {}", authorization.id, user_id);
+                Ok(user_id)
+            }
+            _ => Err(DeviceGrantError::InvalidGrant),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service() -> DeviceAuthService {
+        DeviceAuthService::new(Arc::new(InMemoryDeviceStore::new())).with_verification_uri("https://sirsi.example/device")
+    }
+
+    #[tokio::test]
+    async fn test_device_code_is_approved_once_and_signals_slow_down() {
+        let service = service();
+        let user = Uuid::new_v4();
+        let now = Utc::now();
+        let issued = service.start("sirsi-cli", now).await.unwrap();
+        assert_eq!(issued.interval, DEFAULT_INTERVAL_SECS);
+        assert_eq!(issued.verification_uri_complete, format!("https://sirsi.example/device?user_code={}", issued.user_code));
+
+        assert!(matches!(service.poll(&issued.device_code, "sirsi-cli", now).await, Err(DeviceGrantError::AuthorizationPending)));
+        // Polling inside the interval backs the client off by another step each time
+        let early = now + Duration::seconds(2);
+        assert!(matches!(service.poll(&issued.device_code, "sirsi-cli", early).await, Err(DeviceGrantError::SlowDown { interval: 10 })));
+        let later = early + Duration::seconds(6);
+        assert!(matches!(service.poll(&issued.device_code, "sirsi-cli", later).await, Err(DeviceGrantError::SlowDown { interval: 15 })));
+
+        let typed = issued.user_code.to_lowercase().replace('-', " ");
+        let approved = service.verify(user, &typed, true, later).await.unwrap();
+        assert_eq!(approved.status, DeviceStatus::Approved);
+        assert!(service.verify(user, &issued.user_code, true, later).await.is_err());
+
+        let next = later + Duration::seconds(15);
+        assert_eq!(service.poll(&issued.device_code, "sirsi-cli", next).await.unwrap(), user);
+        let response = service.poll(&issued.device_code, "sirsi-cli", next + Duration::seconds(30)).await.unwrap_err();
+        assert_eq!(response.code(), "invalid_grant");
+    }
+
+    #[tokio::test]
+    async fn test_codes_expire_and_attempts_are_rate_limited() {
+        let service = service().with_code_ttl(Duration::seconds(60));
+        let now = Utc::now();
+        let issued = service.start("sirsi-cli", now).await.unwrap();
+        let expired = now + Duration::seconds(61);
+        assert!(service.verify(Uuid::new_v4(), &issued.user_code, true, expired).await.is_err());
+        let error = service.poll(&issued.device_code, "sirsi-cli", expired).await.unwrap_err();
+        assert_eq!(error.into_response().status(), StatusCode::BAD_REQUEST);
+        assert!(matches!(service.poll(&issued.device_code, "sirsi-cli", expired).await, Err(DeviceGrantError::ExpiredToken)));
+
+        let guesser = Uuid::new_v4();
+        for _ in 0..MAX_VERIFY_FAILURES {
+            assert!(matches!(service.verify(guesser, "BCDF-GHJK", true, now).await, Err(AppError::NotFound(_))));
+        }
+        assert!(matches!(service.verify(guesser, "BCDF-GHJK", true, now).await, Err(AppError::RateLimited { .. })));
+        for _ in 1..MAX_CODES_PER_CLIENT {
+            service.start("sirsi-cli", now).await.unwrap();
+        }
+        assert!(service.start("sirsi-cli", now).await.is_err());
+        assert!(service.start("sirsi-cli", now + Duration::seconds(LIMIT_WINDOW_SECS)).await.is_ok());
+    }
+}
@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool; // CockroachDB uses PostgreSQL protocol
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use super::{DeviceAuthorization, DeviceStatus, DeviceStore};
+use crate::error::{AppError, AppResult};
+
+const COLUMNS: &str =
+    "id, device_code_hash, user_code, client_id, status, user_id, interval_secs, last_polled_at, expires_at, created_at";
+
+pub struct PgDeviceStore {
+    pool: PgPool,
+}
+
+impl PgDeviceStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    async fn transition(&self, id: Uuid, from: DeviceStatus, to: DeviceStatus, user_id: Option<Uuid>) -> AppResult<bool> {
+        let result = sqlx::query(
+            "UPDATE device_authorizations SET status = $1, user_id = COALESCE($2, user_id) WHERE id = $3 AND status = $4"
+        )
+        .bind(to)
+        .bind(user_id)
+        .bind(id)
+        .bind(from)
+        .execute(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(result.rows_affected() == 1)
+    }
+}
+
+#[async_trait]
+impl DeviceStore for PgDeviceStore {
+    async fn insert(&self, authorization: &DeviceAuthorization) -> AppResult<()> {
+        sqlx::query(
+            r#"INSERT INTO device_authorizations (id, device_code_hash, user_code, client_id, status, interval_secs, expires_at, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#
+        )
+        .bind(authorization.id)
+        .bind(&authorization.device_code_hash)
+        .bind(&authorization.user_code)
+        .bind(&authorization.client_id)
+        .bind(authorization.status)
+        .bind(authorization.interval_secs)
+        .bind(authorization.expires_at)
+        .bind(authorization.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(())
+    }
+
+    async fn find_by_device_code(&self, device_code_hash: &str) -> AppResult<Option<DeviceAuthorization>> {
+        let sql = format!("SELECT {} FROM device_authorizations WHERE device_code_hash = $1", COLUMNS);
+        let authorization = sqlx::query_as::<_, DeviceAuthorization>(&sql)
+            .bind(device_code_hash)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(authorization)
+    }
+
+    async fn find_pending_by_user_code(&self, user_code: &str, now: DateTime<Utc>) -> AppResult<Option<DeviceAuthorization>> {
+        let sql = format!(
+            "SELECT {} FROM device_authorizations WHERE user_code = $1 AND status = 'pending' AND expires_at > $2",
+            COLUMNS
+        );
+        let authorization = sqlx::query_as::<_, DeviceAuthorization>(&sql)
+            .bind(user_code)
+            .bind(now)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(authorization)
+    }
+
+    async fn approve(&self, id: Uuid, user_id: Uuid) -> AppResult<bool> {
+        self.transition(id, DeviceStatus::Pending, DeviceStatus::Approved, Some(user_id)).await
+    }
+
+    async fn deny(&self, id: Uuid, user_id: Uuid) -> AppResult<bool> {
+        self.transition(id, DeviceStatus::Pending, DeviceStatus::Denied, Some(user_id)).await
+    }
+
+    async fn record_poll(&self, id: Uuid, at: DateTime<Utc>, interval_secs: i64) -> AppResult<()> {
+        sqlx::query("UPDATE device_authorizations SET last_polled_at = $1, interval_secs = $2 WHERE id = $3")
+            .bind(at)
+            .bind(interval_secs)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(())
+    }
+
+    async fn consume(&self, id: Uuid) -> AppResult<bool> {
+        self.transition(id, DeviceStatus::Approved, DeviceStatus::Consumed, None).await
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryDeviceStore {
+    authorizations: Mutex<HashMap<Uuid, DeviceAuthorization>>,
+}
+
+impl InMemoryDeviceStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    async fn transition(&self, id: Uuid, from: DeviceStatus, to: DeviceStatus, user_id: Option<Uuid>) -> bool {
+        let mut authorizations = self.authorizations.lock().await;
+        match authorizations.get_mut(&id).filter(|a| a.status == from) {
+            Some(authorization) => {
+                authorization.status = to;
+                authorization.user_id = user_id.or(authorization.user_id);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[async_trait]
+impl DeviceStore for InMemoryDeviceStore {
+    async fn insert(&self, authorization: &DeviceAuthorization) -> AppResult<()> {
+        self.authorizations.lock().await.insert(authorization.id, authorization.clone());
+        Ok(())
+    }
+
+    async fn find_by_device_code(&self, device_code_hash: &str) -> AppResult<Option<DeviceAuthorization>> {
+        Ok(self.authorizations.lock().await.values().find(|a| a.device_code_hash == device_code_hash).cloned())
+    }
+
+    async fn find_pending_by_user_code(&self, user_code: &str, now: DateTime<Utc>) -> AppResult<Option<DeviceAuthorization>> {
+        Ok(self
+            .authorizations
+            .lock()
+            .await
+            .values()
+            .find(|a| a.user_code == user_code && a.status == DeviceStatus::Pending && a.expires_at > now)
+            .cloned())
+    }
+
+    async fn approve(&self, id: Uuid, user_id: Uuid) -> AppResult<bool> {
+        Ok(self.transition(id, DeviceStatus::Pending, DeviceStatus::Approved, Some(user_id)).await)
+    }
+
+    async fn deny(&self, id: Uuid, user_id: Uuid) -> AppResult<bool> {
+        Ok(self.transition(id, DeviceStatus::Pending, DeviceStatus::Denied, Some(user_id)).await)
+    }
+
+    async fn record_poll(&self, id: Uuid, at: DateTime<Utc>, interval_secs: i64) -> AppResult<()> {
+        if let Some(authorization) = self.authorizations.lock().await.get_mut(&id) {
+            authorization.last_polled_at = Some(at);
+            authorization.interval_secs = interval_secs;
+        }
+        Ok(())
+    }
+
+    async fn consume(&self, id: Uuid) -> AppResult<bool> {
+        Ok(self.transition(id, DeviceStatus::Approved, DeviceStatus::Consumed, None).await)
+    }
+}
@@ -0,0 +1,257 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use futures::stream::{BoxStream, StreamExt};
+use serde_json::Value;
+use sirsi_key_vault::tenant::{EncryptedField, EncryptedFieldSource, FieldCiphertext, TenantKeyService};
+use sirsi_key_vault::{KeyVaultError, KeyVaultResult};
+use sqlx::types::Json;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::{DiscoveredResource, DiscoveryRun, DiscoveryStore, NewDiscoveryRun};
+use crate::error::{AppError, AppResult};
+
+// Sealed attributes are stored as a single-key object so the column stays valid JSON
+pub const SEALED_ATTRIBUTES_KEY: &str = "$sirsi_enc";
+
+const ATTRIBUTES_SOURCE: &str = "discovery_run_resources.attributes";
+
+// Binds the ciphertext to its row, so attributes can't be swapped between resources
+fn attributes_context(resource_key: &str, region: &str) -> String {
+    format!("{}:{}/{}", ATTRIBUTES_SOURCE, resource_key, region)
+}
+
+fn sealed_attributes(attributes: &BTreeMap<String, Value>) -> Option<&str> {
+    match (attributes.len(), attributes.get(SEALED_ATTRIBUTES_KEY)) {
+        (1, Some(Value::String(ciphertext))) => Some(ciphertext),
+        _ => None,
+    }
+}
+
+fn vault_error(e: KeyVaultError) -> AppError {
+    match e {
+        KeyVaultError::Permission(msg) => AppError::Forbidden(msg),
+        KeyVaultError::NotFound(msg) => AppError::NotFound(msg),
+        e => AppError::Internal(format!("Tenant key operation failed: {}", e)),
+    }
+}
+
+// Discovered attributes carry provider configuration (connection strings, user data, policy
+// documents), so they're sealed with the tenant's key before they reach the inner store. Rows
+// written before encryption was enabled are read back as they are.
+pub struct EncryptedDiscoveryStore {
+    inner: Arc<dyn DiscoveryStore>,
+    keys: Arc<TenantKeyService>,
+}
+
+impl EncryptedDiscoveryStore {
+    pub fn new(inner: Arc<dyn DiscoveryStore>, keys: Arc<TenantKeyService>) -> Self {
+        Self { inner, keys }
+    }
+
+    async fn open(&self, mut resource: DiscoveredResource) -> AppResult<DiscoveredResource> {
+        let Some(ciphertext) = sealed_attributes(&resource.attributes) else {
+            return Ok(resource);
+        };
+        // Runs are read by id alone; the tenant comes from the ciphertext and the key service
+        // holds it to that tenant's key
+        let tenant = ciphertext.parse::<FieldCiphertext>().map_err(vault_error)?.tenant;
+        let context = attributes_context(&resource.resource_key, &resource.region);
+        let plaintext = self.keys.decrypt_field(&tenant, &context, ciphertext).await.map_err(vault_error)?;
+        resource.attributes = serde_json::from_slice(&plaintext)
+            .map_err(|e| AppError::Serialization(format!("Decrypted attributes are not valid JSON: {}", e)))?;
+        Ok(resource)
+    }
+}
+
+#[async_trait]
+impl DiscoveryStore for EncryptedDiscoveryStore {
+    async fn create_run(
+        &self,
+        tenant_id: Uuid,
+        run: &NewDiscoveryRun,
+        started_at: DateTime<Utc>,
+        completed_at: DateTime<Utc>,
+    ) -> AppResult<DiscoveryRun> {
+        let tenant = tenant_id.to_string();
+        let mut sealed = run.clone();
+        for resource in sealed.resources.iter_mut() {
+            let plaintext = serde_json::to_vec(&resource.attributes)
+                .map_err(|e| AppError::Serialization(e.to_string()))?;
+            let context = attributes_context(&resource.resource_key, &resource.region);
+            let ciphertext = self.keys.encrypt_field(&tenant, &context, &plaintext).await.map_err(vault_error)?;
+            resource.attributes = BTreeMap::from([(SEALED_ATTRIBUTES_KEY.to_string(), Value::String(ciphertext))]);
+        }
+        self.inner.create_run(tenant_id, &sealed, started_at, completed_at).await
+    }
+
+    async fn get_run(&self, tenant_id: Uuid, run_id: Uuid) -> AppResult<Option<DiscoveryRun>> {
+        self.inner.get_run(tenant_id, run_id).await
+    }
+
+    async fn list_runs(&self, tenant_id: Uuid, limit: i64) -> AppResult<Vec<DiscoveryRun>> {
+        self.inner.list_runs(tenant_id, limit).await
+    }
+
+    async fn previous_run(&self, run: &DiscoveryRun) -> AppResult<Option<DiscoveryRun>> {
+        self.inner.previous_run(run).await
+    }
+
+    fn resources(&self, run_id: Uuid) -> BoxStream<'_, AppResult<DiscoveredResource>> {
+        self.inner
+            .resources(run_id)
+            .then(move |resource| async move { self.open(resource?).await })
+            .boxed()
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct SealedRow {
+    run_id: Uuid,
+    resource_key: String,
+    region: String,
+    ciphertext: String,
+}
+
+// Row ids are `<run id>|<resource key>|<region>`; run ids are fixed width and regions never
+// contain `|`, so the parts split back out unambiguously
+fn split_row_id(row_id: &str) -> KeyVaultResult<(Uuid, &str, &str)> {
+    let malformed = || KeyVaultError::Validation(format!("Malformed discovery row id {}", row_id));
+    let (run_id, rest) = row_id.split_once('|').ok_or_else(malformed)?;
+    let (resource_key, region) = rest.rsplit_once('|').ok_or_else(malformed)?;
+    Ok((run_id.parse().map_err(|_| malformed())?, resource_key, region))
+}
+
+// Lets a `Reencryptor` move sealed discovery attributes onto a tenant's current key
+pub struct PgDiscoveryAttributeSource {
+    pool: PgPool,
+}
+
+impl PgDiscoveryAttributeSource {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl EncryptedFieldSource for PgDiscoveryAttributeSource {
+    fn name(&self) -> &str {
+        ATTRIBUTES_SOURCE
+    }
+
+    async fn page(&self, tenant: &str, after: Option<&str>, limit: usize) -> KeyVaultResult<Vec<EncryptedField>> {
+        let tenant_id: Uuid = tenant
+            .parse()
+            .map_err(|_| KeyVaultError::Validation(format!("Invalid tenant id {}", tenant)))?;
+        let after = after.map(split_row_id).transpose()?;
+        let rows = sqlx::query_as::<_, SealedRow>(
+            r#"SELECT r.run_id, r.resource_key, r.region, r.attributes->>'$sirsi_enc' AS ciphertext
+            FROM discovery_run_resources r JOIN discovery_runs d ON d.id = r.run_id
+            WHERE d.tenant_id = $1 AND r.attributes ? '$sirsi_enc'
+              AND ($2::UUID IS NULL OR (r.run_id, r.resource_key, r.region) > ($2, $3, $4))
+            ORDER BY r.run_id, r.resource_key, r.region
+            LIMIT $5"#,
+        )
+        .bind(tenant_id)
+        .bind(after.map(|(run_id, _, _)| run_id))
+        .bind(after.map(|(_, key, _)| key))
+        .bind(after.map(|(_, _, region)| region))
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| EncryptedField {
+                row_id: format!("{}|{}|{}", row.run_id, row.resource_key, row.region),
+                context: attributes_context(&row.resource_key, &row.region),
+                ciphertext: row.ciphertext,
+            })
+            .collect())
+    }
+
+    async fn replace(&self, _tenant: &str, row_id: &str, current: &str, replacement: &str) -> KeyVaultResult<bool> {
+        let (run_id, resource_key, region) = split_row_id(row_id)?;
+        let sealed = BTreeMap::from([(SEALED_ATTRIBUTES_KEY, replacement)]);
+        let result = sqlx::query(
+            r#"UPDATE discovery_run_resources SET attributes = $1
+            WHERE run_id = $2 AND resource_key = $3 AND region = $4 AND attributes->>'$sirsi_enc' = $5"#,
+        )
+        .bind(Json(sealed))
+        .bind(run_id)
+        .bind(resource_key)
+        .bind(region)
+        .bind(current)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discovery::InMemoryDiscoveryStore;
+    use sirsi_key_vault::secret::{
+        AccessPolicy, AccessPolicyManager, InMemoryAccessPolicyManager, InMemoryAuditLogger, SecretAction, SecretPermission,
+    };
+
+    #[tokio::test]
+    async fn test_attributes_sealed_per_tenant() {
+        let policies = InMemoryAccessPolicyManager::new();
+        policies
+            .create_policy(AccessPolicy {
+                id: "tenant-keys".into(),
+                name: "tenant-keys".into(),
+                description: None,
+                principals: vec!["svc-core".into()],
+                permissions: vec![SecretPermission {
+                    actions: vec![SecretAction::Write],
+                    secret_patterns: vec!["tenant-keys/*".into()],
+                }],
+                conditions: None,
+            })
+            .await
+            .unwrap();
+        let keys = Arc::new(TenantKeyService::new(Arc::new(policies), Arc::new(InMemoryAuditLogger::new())));
+        let (acme, globex) = (Uuid::new_v4(), Uuid::new_v4());
+        keys.create_tenant_key("svc-core", &acme.to_string()).await.unwrap();
+        keys.create_tenant_key("svc-core", &globex.to_string()).await.unwrap();
+
+        let inner = Arc::new(InMemoryDiscoveryStore::new());
+        let store = EncryptedDiscoveryStore::new(inner.clone(), keys.clone());
+        let resource = DiscoveredResource {
+            resource_key: "arn:aws:rds:us-east-1:123:db:orders".into(),
+            region: "us-east-1".into(),
+            resource_type: "rds_instance".into(),
+            name: Some("orders".into()),
+            tags: BTreeMap::new(),
+            attributes: BTreeMap::from([("master_password".to_string(), Value::from("hunter2"))]),
+        };
+        let run = NewDiscoveryRun {
+            provider: "aws".into(),
+            account_id: "123".into(),
+            started_at: None,
+            resources: vec![resource.clone()],
+        };
+        let now = Utc::now();
+        let saved = store.create_run(acme, &run, now, now).await.unwrap();
+
+        let stored: Vec<_> = inner.resources(saved.id).collect().await;
+        let stored = stored.into_iter().next().unwrap().unwrap();
+        assert!(sealed_attributes(&stored.attributes).is_some());
+        assert!(!serde_json::to_string(&stored.attributes).unwrap().contains("hunter2"));
+
+        let read: Vec<_> = store.resources(saved.id).collect().await;
+        assert_eq!(read.into_iter().next().unwrap().unwrap(), resource);
+
+        // Globex's key can't open Acme's attributes
+        let ciphertext = sealed_attributes(&stored.attributes).unwrap();
+        let context = attributes_context(&resource.resource_key, &resource.region);
+        assert!(keys.decrypt_field(&globex.to_string(), &context, ciphertext).await.is_err());
+    }
+}
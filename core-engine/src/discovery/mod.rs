@@ -0,0 +1,444 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use futures::stream::{BoxStream, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::compliance::ComplianceService;
+use crate::error::{AppError, AppResult};
+use crate::inventory::InventoryService;
+
+pub mod encrypted;
+pub mod store;
+
+pub use encrypted::{EncryptedDiscoveryStore, PgDiscoveryAttributeSource};
+pub use store::{InMemoryDiscoveryStore, PgDiscoveryStore};
+
+// Attributes that change on every scan without the resource itself changing
+const DEFAULT_IGNORED_FIELDS: &[&str] = &[
+    "attributes.*_at",
+    "attributes.*_time",
+    "attributes.*timestamp",
+    "attributes.last_seen",
+];
+
+// A resource as seen by one discovery run, normalized across providers. Resources are
+// identified by `resource_key` (the ARN, or `<type>/<id>` where there is none) plus region,
+// so a resource that moved regions shows up as removed and added.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DiscoveredResource {
+    pub resource_key: String,
+    pub region: String,
+    pub resource_type: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub tags: BTreeMap<String, String>,
+    #[serde(default)]
+    pub attributes: BTreeMap<String, Value>,
+}
+
+impl DiscoveredResource {
+    pub fn new(resource_type: &str, resource_id: &str, arn: Option<&str>, region: &str) -> Self {
+        Self {
+            resource_key: arn.map_or_else(|| format!("{}/{}", resource_type, resource_id), str::to_string),
+            region: region.to_string(),
+            resource_type: resource_type.to_string(),
+            name: None,
+            tags: BTreeMap::new(),
+            attributes: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_name(mut self, name: Option<String>) -> Self {
+        self.name = name;
+        self
+    }
+
+    pub fn with_tags(mut self, tags: impl IntoIterator<Item = (String, String)>) -> Self {
+        self.tags.extend(tags);
+        self
+    }
+
+    pub fn with_attribute(mut self, key: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.attributes.insert(key.into(), value.into());
+        self
+    }
+
+    fn identity(&self) -> (&str, &str) {
+        (&self.resource_key, &self.region)
+    }
+
+    // Flattened to `tags.<key>` / `attributes.<key>` so changes are reported per field
+    fn fields(&self) -> BTreeMap<String, Value> {
+        let mut fields = BTreeMap::new();
+        fields.insert("resource_type".to_string(), Value::String(self.resource_type.clone()));
+        if let Some(name) = &self.name {
+            fields.insert("name".to_string(), Value::String(name.clone()));
+        }
+        for (key, value) in &self.tags {
+            fields.insert(format!("tags.{}", key), Value::String(value.clone()));
+        }
+        for (key, value) in &self.attributes {
+            fields.insert(format!("attributes.{}", key), value.clone());
+        }
+        fields
+    }
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct DiscoveryRun {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub provider: String,
+    pub account_id: String,
+    pub resource_count: i64,
+    pub started_at: DateTime<Utc>,
+    pub completed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewDiscoveryRun {
+    pub provider: String,
+    pub account_id: String,
+    pub started_at: Option<DateTime<Utc>>,
+    pub resources: Vec<DiscoveredResource>,
+}
+
+// Field names excluded from diffs; a single `*` matches any run of characters
+#[derive(Debug, Clone)]
+pub struct IgnoreList {
+    patterns: Vec<String>,
+}
+
+impl Default for IgnoreList {
+    fn default() -> Self {
+        Self::new(DEFAULT_IGNORED_FIELDS.iter().copied())
+    }
+}
+
+impl IgnoreList {
+    pub fn new<S: Into<String>>(patterns: impl IntoIterator<Item = S>) -> Self {
+        Self { patterns: patterns.into_iter().map(Into::into).collect() }
+    }
+
+    pub fn with_pattern(mut self, pattern: impl Into<String>) -> Self {
+        self.patterns.push(pattern.into());
+        self
+    }
+
+    pub fn ignores(&self, field: &str) -> bool {
+        self.patterns.iter().any(|pattern| match pattern.split_once('*') {
+            Some((prefix, suffix)) => {
+                field.len() >= prefix.len() + suffix.len() && field.starts_with(prefix) && field.ends_with(suffix)
+            }
+            None => field == pattern,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ResourceChange {
+    pub resource_key: String,
+    pub region: String,
+    pub resource_type: String,
+    pub changes: Vec<FieldChange>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ResourceDiff {
+    pub added: Vec<DiscoveredResource>,
+    pub removed: Vec<DiscoveredResource>,
+    pub changed: Vec<ResourceChange>,
+    pub unchanged: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RunDiff {
+    pub base_run_id: Uuid,
+    pub run_id: Uuid,
+    #[serde(flatten)]
+    pub diff: ResourceDiff,
+}
+
+fn field_changes(before: &DiscoveredResource, after: &DiscoveredResource, ignore: &IgnoreList) -> Vec<FieldChange> {
+    let (before, after) = (before.fields(), after.fields());
+    let names: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    names
+        .into_iter()
+        .filter(|name| !ignore.ignores(name))
+        .filter_map(|name| {
+            let (old, new) = (before.get(name), after.get(name));
+            (old != new).then(|| FieldChange { field: name.clone(), before: old.cloned(), after: new.cloned() })
+        })
+        .collect()
+}
+
+// Merges two runs streamed in (resource_key, region) order, so only the differences are held
+// in memory rather than either run
+pub async fn diff_resources(
+    mut before: BoxStream<'_, AppResult<DiscoveredResource>>,
+    mut after: BoxStream<'_, AppResult<DiscoveredResource>>,
+    ignore: &IgnoreList,
+) -> AppResult<ResourceDiff> {
+    let mut diff = ResourceDiff::default();
+    let mut old = before.next().await.transpose()?;
+    let mut new = after.next().await.transpose()?;
+    loop {
+        let ordering = match (&old, &new) {
+            (None, None) => break,
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(o), Some(n)) => o.identity().cmp(&n.identity()),
+        };
+        match ordering {
+            Ordering::Less => {
+                diff.removed.extend(old.take());
+                old = before.next().await.transpose()?;
+            }
+            Ordering::Greater => {
+                diff.added.extend(new.take());
+                new = after.next().await.transpose()?;
+            }
+            Ordering::Equal => {
+                let (o, n) = (old.take().unwrap(), new.take().unwrap());
+                let changes = field_changes(&o, &n, ignore);
+                if changes.is_empty() {
+                    diff.unchanged += 1;
+                } else {
+                    diff.changed.push(ResourceChange {
+                        resource_key: n.resource_key,
+                        region: n.region,
+                        resource_type: n.resource_type,
+                        changes,
+                    });
+                }
+                old = before.next().await.transpose()?;
+                new = after.next().await.transpose()?;
+            }
+        }
+    }
+    Ok(diff)
+}
+
+#[async_trait]
+pub trait DiscoveryStore: Send + Sync {
+    // `resources` arrive sorted by (resource_key, region) with no duplicates
+    async fn create_run(
+        &self,
+        tenant_id: Uuid,
+        run: &NewDiscoveryRun,
+        started_at: DateTime<Utc>,
+        completed_at: DateTime<Utc>,
+    ) -> AppResult<DiscoveryRun>;
+    async fn get_run(&self, tenant_id: Uuid, run_id: Uuid) -> AppResult<Option<DiscoveryRun>>;
+    async fn list_runs(&self, tenant_id: Uuid, limit: i64) -> AppResult<Vec<DiscoveryRun>>;
+    // The latest earlier run for the same tenant, provider and account
+    async fn previous_run(&self, run: &DiscoveryRun) -> AppResult<Option<DiscoveryRun>>;
+    // Must yield in (resource_key, region) order
+    fn resources(&self, run_id: Uuid) -> BoxStream<'_, AppResult<DiscoveredResource>>;
+}
+
+pub struct DiscoveryService {
+    store: Arc<dyn DiscoveryStore>,
+    ignore: IgnoreList,
+    inventory: Option<Arc<InventoryService>>,
+    compliance: Option<Arc<ComplianceService>>,
+}
+
+impl DiscoveryService {
+    pub fn new(store: Arc<dyn DiscoveryStore>) -> Self {
+        Self { store, ignore: IgnoreList::default(), inventory: None, compliance: None }
+    }
+
+    pub fn with_ignore_list(mut self, ignore: IgnoreList) -> Self {
+        self.ignore = ignore;
+        self
+    }
+
+    // Each recorded run also refreshes the searchable inventory and checks saved searches
+    pub fn with_inventory(mut self, inventory: Arc<InventoryService>) -> Self {
+        self.inventory = Some(inventory);
+        self
+    }
+
+    // Rescans the tenant in the background whenever a run changes the inventory
+    pub fn with_compliance(mut self, compliance: Arc<ComplianceService>) -> Self {
+        self.compliance = Some(compliance);
+        self
+    }
+
+    pub async fn record_run(&self, tenant_id: Uuid, mut run: NewDiscoveryRun, now: DateTime<Utc>) -> AppResult<DiscoveryRun> {
+        if run.provider.trim().is_empty() || run.account_id.trim().is_empty() {
+            return Err(AppError::Validation("provider and account_id are required".into()));
+        }
+        run.resources.sort_by(|a, b| a.identity().cmp(&b.identity()));
+        if let Some(pair) = run.resources.windows(2).find(|pair| pair[0].identity() == pair[1].identity()) {
+            return Err(AppError::InvalidInput(format!(
+                "Resource {} in {} appears more than once",
+                pair[0].resource_key, pair[0].region
+            )));
+        }
+        let started_at = run.started_at.unwrap_or(now);
+        let saved = self.store.create_run(tenant_id, &run, started_at, now).await?;
+        // The run is kept either way; the inventory catches up with the account's next run
+        if let Some(inventory) = &self.inventory {
+            match inventory.ingest(&saved, &run.resources).await {
+                Ok(report) if !report.stale => self.start_compliance_scan(tenant_id),
+                Ok(_) => {}
+                Err(e) => warn!("Failed to update inventory from discovery run {}: {}", saved.id, e),
+            }
+        }
+        Ok(saved)
+    }
+
+    fn start_compliance_scan(&self, tenant_id: Uuid) {
+        if let Some(compliance) = self.compliance.clone() {
+            tokio::spawn(async move {
+                if let Err(e) = compliance.scan(tenant_id, Utc::now()).await {
+                    warn!("Compliance scan for tenant {} failed: {}", tenant_id, e);
+                }
+            });
+        }
+    }
+
+    pub async fn runs(&self, tenant_id: Uuid, limit: i64) -> AppResult<Vec<DiscoveryRun>> {
+        self.store.list_runs(tenant_id, limit).await
+    }
+
+    pub async fn run(&self, tenant_id: Uuid, run_id: Uuid) -> AppResult<DiscoveryRun> {
+        self.store
+            .get_run(tenant_id, run_id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Discovery run not found".into()))
+    }
+
+    // What changed going from `base_run_id` to `run_id`
+    pub async fn diff_runs(&self, tenant_id: Uuid, base_run_id: Uuid, run_id: Uuid) -> AppResult<RunDiff> {
+        let base = self.run(tenant_id, base_run_id).await?;
+        let run = self.run(tenant_id, run_id).await?;
+        if (&base.provider, &base.account_id) != (&run.provider, &run.account_id) {
+            return Err(AppError::InvalidInput("Only runs of the same provider account can be compared".into()));
+        }
+        let diff = diff_resources(self.store.resources(base.id), self.store.resources(run.id), &self.ignore).await?;
+        Ok(RunDiff { base_run_id: base.id, run_id: run.id, diff })
+    }
+
+    pub async fn diff_against_previous(&self, tenant_id: Uuid, run_id: Uuid) -> AppResult<RunDiff> {
+        let run = self.run(tenant_id, run_id).await?;
+        let previous = self
+            .store
+            .previous_run(&run)
+            .await?
+            .ok_or_else(|| AppError::NotFound("No earlier run of this provider account to compare with".into()))?;
+        self.diff_runs(tenant_id, previous.id, run.id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    fn instance(id: &str, region: &str) -> DiscoveredResource {
+        let arn = format!("arn:aws:ec2:{}:123456789012:instance/{}", region, id);
+        DiscoveredResource::new("ec2_instance", id, Some(&arn), region)
+            .with_name(Some(id.to_string()))
+            .with_tags([("env".to_string(), "prod".to_string())])
+            .with_attribute("instance_type", "t3.medium")
+    }
+
+    fn run(resources: Vec<DiscoveredResource>) -> NewDiscoveryRun {
+        NewDiscoveryRun { provider: "aws".into(), account_id: "123456789012".into(), started_at: None, resources }
+    }
+
+    #[tokio::test]
+    async fn test_diff_against_previous_run() {
+        let discovery = DiscoveryService::new(Arc::new(InMemoryDiscoveryStore::new()));
+        let tenant = Uuid::new_v4();
+        let monday = Utc.with_ymd_and_hms(2025, 7, 7, 9, 0, 0).unwrap();
+
+        let first = run(vec![
+            instance("i-web", "us-east-1").with_attribute("launch_time", "2025-07-01T00:00:00Z"),
+            instance("i-db", "us-east-1"),
+            instance("i-batch", "us-east-1"),
+        ]);
+        let first = discovery.record_run(tenant, first, monday).await.unwrap();
+        let mut retagged = instance("i-web", "us-east-1").with_attribute("launch_time", "2025-07-07T00:00:00Z");
+        retagged.tags.insert("env".into(), "staging".into());
+        retagged.tags.insert("owner".into(), "platform".into());
+        let second = run(vec![
+            instance("i-batch", "eu-west-1"),
+            retagged,
+            instance("i-db", "us-east-1"),
+            instance("i-cache", "us-east-1"),
+        ]);
+        let second = discovery.record_run(tenant, second, monday + Duration::days(7)).await.unwrap();
+        assert_eq!(second.resource_count, 4);
+
+        let diff = discovery.diff_against_previous(tenant, second.id).await.unwrap();
+        assert_eq!(diff.base_run_id, first.id);
+        assert_eq!(diff.diff.unchanged, 1);
+        // Moving regions is a remove plus an add
+        let added: Vec<_> = diff.diff.added.iter().map(|r| (r.resource_key.as_str(), r.region.as_str())).collect();
+        assert_eq!(added.len(), 2);
+        assert!(added.contains(&("arn:aws:ec2:eu-west-1:123456789012:instance/i-batch", "eu-west-1")));
+        assert_eq!(diff.diff.removed.len(), 1);
+        assert_eq!(diff.diff.removed[0].region, "us-east-1");
+
+        // The launch time moved too, but timestamps are ignored by default
+        assert_eq!(diff.diff.changed.len(), 1);
+        assert_eq!(
+            diff.diff.changed[0].changes,
+            vec![
+                FieldChange { field: "tags.env".into(), before: Some("prod".into()), after: Some("staging".into()) },
+                FieldChange { field: "tags.owner".into(), before: None, after: Some("platform".into()) },
+            ]
+        );
+
+        assert!(matches!(discovery.diff_against_previous(tenant, first.id).await, Err(AppError::NotFound(_))));
+        assert!(matches!(discovery.diff_against_previous(Uuid::new_v4(), second.id).await, Err(AppError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_ignore_list_is_configurable() {
+        let ignore = IgnoreList::new(["tags.*"]);
+        assert!(ignore.ignores("tags.env") && !ignore.ignores("attributes.launch_time"));
+        assert!(IgnoreList::default().ignores("attributes.updated_at"));
+        assert!(!IgnoreList::default().ignores("attributes.at"));
+
+        let before = instance("i-web", "us-east-1").with_attribute("launch_time", "a");
+        let mut after = instance("i-web", "us-east-1").with_attribute("launch_time", "b");
+        after.tags.insert("env".into(), "staging".into());
+        let stream = |r: &DiscoveredResource| futures::stream::iter(vec![Ok(r.clone())]).boxed();
+
+        let diff = diff_resources(stream(&before), stream(&after), &ignore).await.unwrap();
+        assert_eq!(diff.changed[0].changes.len(), 1);
+        assert_eq!(diff.changed[0].changes[0].field, "attributes.launch_time");
+        let diff = diff_resources(stream(&before), stream(&after), &ignore.with_pattern("attributes.launch_time"))
+            .await
+            .unwrap();
+        assert!(diff.changed.is_empty());
+        assert_eq!(diff.unchanged, 1);
+
+        let discovery = DiscoveryService::new(Arc::new(InMemoryDiscoveryStore::new()));
+        let duplicate = run(vec![instance("i-web", "us-east-1"), instance("i-web", "us-east-1")]);
+        assert!(matches!(
+            discovery.record_run(Uuid::new_v4(), duplicate, Utc::now()).await,
+            Err(AppError::InvalidInput(_))
+        ));
+    }
+}
@@ -0,0 +1,230 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use futures::stream::{self, BoxStream, StreamExt};
+use serde_json::Value;
+use sqlx::types::Json;
+use sqlx::{PgPool, QueryBuilder}; // CockroachDB uses PostgreSQL protocol
+use uuid::Uuid;
+
+use super::{DiscoveredResource, DiscoveryRun, DiscoveryStore, NewDiscoveryRun};
+use crate::error::{AppError, AppResult};
+
+// Rows per INSERT when saving a run's resources
+const INSERT_BATCH: usize = 500;
+
+#[derive(sqlx::FromRow)]
+struct ResourceRow {
+    resource_key: String,
+    region: String,
+    resource_type: String,
+    name: Option<String>,
+    tags: Json<BTreeMap<String, String>>,
+    attributes: Json<BTreeMap<String, Value>>,
+}
+
+impl From<ResourceRow> for DiscoveredResource {
+    fn from(row: ResourceRow) -> Self {
+        Self {
+            resource_key: row.resource_key,
+            region: row.region,
+            resource_type: row.resource_type,
+            name: row.name,
+            tags: row.tags.0,
+            attributes: row.attributes.0,
+        }
+    }
+}
+
+pub struct PgDiscoveryStore {
+    pool: PgPool,
+}
+
+impl PgDiscoveryStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl DiscoveryStore for PgDiscoveryStore {
+    async fn create_run(
+        &self,
+        tenant_id: Uuid,
+        run: &NewDiscoveryRun,
+        started_at: DateTime<Utc>,
+        completed_at: DateTime<Utc>,
+    ) -> AppResult<DiscoveryRun> {
+        let mut tx = self.pool.begin().await.map_err(AppError::Database)?;
+        let saved = sqlx::query_as::<_, DiscoveryRun>(
+            r#"INSERT INTO discovery_runs (tenant_id, provider, account_id, resource_count, started_at, completed_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id, tenant_id, provider, account_id, resource_count, started_at, completed_at"#
+        )
+        .bind(tenant_id)
+        .bind(&run.provider)
+        .bind(&run.account_id)
+        .bind(run.resources.len() as i64)
+        .bind(started_at)
+        .bind(completed_at)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(AppError::Database)?;
+
+        for batch in run.resources.chunks(INSERT_BATCH) {
+            let mut insert = QueryBuilder::new(
+                "INSERT INTO discovery_run_resources (run_id, resource_key, region, resource_type, name, tags, attributes) ",
+            );
+            insert.push_values(batch, |mut row, resource| {
+                row.push_bind(saved.id)
+                    .push_bind(&resource.resource_key)
+                    .push_bind(&resource.region)
+                    .push_bind(&resource.resource_type)
+                    .push_bind(&resource.name)
+                    .push_bind(Json(&resource.tags))
+                    .push_bind(Json(&resource.attributes));
+            });
+            insert.build().execute(&mut *tx).await.map_err(AppError::Database)?;
+        }
+        tx.commit().await.map_err(AppError::Database)?;
+
+        Ok(saved)
+    }
+
+    async fn get_run(&self, tenant_id: Uuid, run_id: Uuid) -> AppResult<Option<DiscoveryRun>> {
+        let run = sqlx::query_as::<_, DiscoveryRun>(
+            r#"SELECT id, tenant_id, provider, account_id, resource_count, started_at, completed_at
+            FROM discovery_runs WHERE id = $1 AND tenant_id = $2"#
+        )
+        .bind(run_id)
+        .bind(tenant_id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(run)
+    }
+
+    async fn list_runs(&self, tenant_id: Uuid, limit: i64) -> AppResult<Vec<DiscoveryRun>> {
+        let runs = sqlx::query_as::<_, DiscoveryRun>(
+            r#"SELECT id, tenant_id, provider, account_id, resource_count, started_at, completed_at
+            FROM discovery_runs WHERE tenant_id = $1
+            ORDER BY completed_at DESC LIMIT $2"#
+        )
+        .bind(tenant_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(runs)
+    }
+
+    async fn previous_run(&self, run: &DiscoveryRun) -> AppResult<Option<DiscoveryRun>> {
+        let previous = sqlx::query_as::<_, DiscoveryRun>(
+            r#"SELECT id, tenant_id, provider, account_id, resource_count, started_at, completed_at
+            FROM discovery_runs
+            WHERE tenant_id = $1 AND provider = $2 AND account_id = $3 AND completed_at < $4
+            ORDER BY completed_at DESC LIMIT 1"#
+        )
+        .bind(run.tenant_id)
+        .bind(&run.provider)
+        .bind(&run.account_id)
+        .bind(run.completed_at)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(previous)
+    }
+
+    // CockroachDB compares strings bytewise, the same order the diff merge relies on
+    fn resources(&self, run_id: Uuid) -> BoxStream<'_, AppResult<DiscoveredResource>> {
+        sqlx::query_as::<_, ResourceRow>(
+            r#"SELECT resource_key, region, resource_type, name, tags, attributes
+            FROM discovery_run_resources WHERE run_id = $1
+            ORDER BY resource_key, region"#
+        )
+        .bind(run_id)
+        .fetch(&self.pool)
+        .map(|row| row.map(DiscoveredResource::from).map_err(AppError::Database))
+        .boxed()
+    }
+}
+
+#[derive(Default)]
+struct DiscoveryState {
+    runs: Vec<DiscoveryRun>,
+    resources: HashMap<Uuid, Vec<DiscoveredResource>>,
+}
+
+#[derive(Default)]
+pub struct InMemoryDiscoveryStore {
+    state: Mutex<DiscoveryState>,
+}
+
+impl InMemoryDiscoveryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl DiscoveryStore for InMemoryDiscoveryStore {
+    async fn create_run(
+        &self,
+        tenant_id: Uuid,
+        run: &NewDiscoveryRun,
+        started_at: DateTime<Utc>,
+        completed_at: DateTime<Utc>,
+    ) -> AppResult<DiscoveryRun> {
+        let saved = DiscoveryRun {
+            id: Uuid::new_v4(),
+            tenant_id,
+            provider: run.provider.clone(),
+            account_id: run.account_id.clone(),
+            resource_count: run.resources.len() as i64,
+            started_at,
+            completed_at,
+        };
+        let mut state = self.state.lock().unwrap();
+        state.runs.push(saved.clone());
+        state.resources.insert(saved.id, run.resources.clone());
+        Ok(saved)
+    }
+
+    async fn get_run(&self, tenant_id: Uuid, run_id: Uuid) -> AppResult<Option<DiscoveryRun>> {
+        let state = self.state.lock().unwrap();
+        Ok(state.runs.iter().find(|r| r.id == run_id && r.tenant_id == tenant_id).cloned())
+    }
+
+    async fn list_runs(&self, tenant_id: Uuid, limit: i64) -> AppResult<Vec<DiscoveryRun>> {
+        let state = self.state.lock().unwrap();
+        let mut runs: Vec<DiscoveryRun> = state.runs.iter().filter(|r| r.tenant_id == tenant_id).cloned().collect();
+        runs.sort_by_key(|r| std::cmp::Reverse(r.completed_at));
+        runs.truncate(limit.max(0) as usize);
+        Ok(runs)
+    }
+
+    async fn previous_run(&self, run: &DiscoveryRun) -> AppResult<Option<DiscoveryRun>> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .runs
+            .iter()
+            .filter(|r| {
+                r.tenant_id == run.tenant_id
+                    && r.provider == run.provider
+                    && r.account_id == run.account_id
+                    && r.completed_at < run.completed_at
+            })
+            .max_by_key(|r| r.completed_at)
+            .cloned())
+    }
+
+    fn resources(&self, run_id: Uuid) -> BoxStream<'_, AppResult<DiscoveredResource>> {
+        let resources = self.state.lock().unwrap().resources.get(&run_id).cloned().unwrap_or_default();
+        stream::iter(resources.into_iter().map(Ok)).boxed()
+    }
+}
@@ -0,0 +1,497 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use uuid::Uuid;
+
+use crate::error::{AppError, AppResult};
+use crate::metering::{Acquire, MeteringService, Quota};
+
+pub mod store;
+
+pub use store::{InMemoryEntitlementStore, PgEntitlementStore};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "VARCHAR", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum PlanTier {
+    Free,
+    Pro,
+    Enterprise,
+}
+
+impl PlanTier {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PlanTier::Free => "free",
+            PlanTier::Pro => "pro",
+            PlanTier::Enterprise => "enterprise",
+        }
+    }
+}
+
+// Capabilities gated by plan. Limit-type features are counted with the metering quota counters,
+// one unit per holder (e.g. `aws/123456789012` for a cloud account)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Feature {
+    CloudAccounts,
+    AutoMl,
+    MeshManagement,
+    SsoProviders,
+}
+
+impl Feature {
+    pub const ALL: &'static [Feature] =
+        &[Feature::CloudAccounts, Feature::AutoMl, Feature::MeshManagement, Feature::SsoProviders];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Feature::CloudAccounts => "cloud_accounts",
+            Feature::AutoMl => "auto_ml",
+            Feature::MeshManagement => "mesh_management",
+            Feature::SsoProviders => "sso_providers",
+        }
+    }
+
+    // The counter behind a limit-type feature; `None` for on/off features
+    pub fn quota(&self) -> Option<Quota> {
+        match self {
+            Feature::CloudAccounts => Some(Quota::CloudAccounts),
+            Feature::SsoProviders => Some(Quota::SsoProviders),
+            Feature::AutoMl | Feature::MeshManagement => None,
+        }
+    }
+
+    fn denied(&self) -> Entitlement {
+        match self.quota() {
+            Some(_) => Entitlement::Limit { limit: Some(0) },
+            None => Entitlement::Flag { enabled: false },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Entitlement {
+    Flag { enabled: bool },
+    // A missing limit means unlimited
+    Limit { limit: Option<i64> },
+}
+
+impl Entitlement {
+    // Whether a tenant holding `count` units (ignored for flags) is within the entitlement
+    pub fn allows(&self, count: i64) -> bool {
+        match self {
+            Entitlement::Flag { enabled } => *enabled,
+            Entitlement::Limit { limit } => limit.map_or(true, |limit| count <= limit),
+        }
+    }
+
+    fn limit(&self) -> Option<i64> {
+        match self {
+            Entitlement::Flag { .. } => None,
+            Entitlement::Limit { limit } => *limit,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Plan {
+    pub tier: PlanTier,
+    pub name: String,
+    // Features missing from a plan are not included in it
+    pub entitlements: BTreeMap<Feature, Entitlement>,
+}
+
+impl Plan {
+    pub fn entitlement(&self, feature: Feature) -> Entitlement {
+        self.entitlements.get(&feature).copied().unwrap_or_else(|| feature.denied())
+    }
+}
+
+// Plans in upgrade order, cheapest first
+#[derive(Debug, Clone, Serialize)]
+pub struct PlanCatalog {
+    plans: Vec<Plan>,
+}
+
+impl Default for PlanCatalog {
+    fn default() -> Self {
+        let plan = |tier: PlanTier, name: &str, entitlements: &[(Feature, Entitlement)]| Plan {
+            tier,
+            name: name.to_string(),
+            entitlements: entitlements.iter().copied().collect(),
+        };
+        Self::new(vec![
+            plan(
+                PlanTier::Free,
+                "Free",
+                &[
+                    (Feature::CloudAccounts, Entitlement::Limit { limit: Some(1) }),
+                    (Feature::AutoMl, Entitlement::Flag { enabled: false }),
+                    (Feature::MeshManagement, Entitlement::Flag { enabled: false }),
+                    (Feature::SsoProviders, Entitlement::Limit { limit: Some(0) }),
+                ],
+            ),
+            plan(
+                PlanTier::Pro,
+                "Pro",
+                &[
+                    (Feature::CloudAccounts, Entitlement::Limit { limit: Some(10) }),
+                    (Feature::AutoMl, Entitlement::Flag { enabled: true }),
+                    (Feature::MeshManagement, Entitlement::Flag { enabled: false }),
+                    (Feature::SsoProviders, Entitlement::Limit { limit: Some(1) }),
+                ],
+            ),
+            plan(
+                PlanTier::Enterprise,
+                "Enterprise",
+                &[
+                    (Feature::CloudAccounts, Entitlement::Limit { limit: None }),
+                    (Feature::AutoMl, Entitlement::Flag { enabled: true }),
+                    (Feature::MeshManagement, Entitlement::Flag { enabled: true }),
+                    (Feature::SsoProviders, Entitlement::Limit { limit: None }),
+                ],
+            ),
+        ])
+    }
+}
+
+impl PlanCatalog {
+    pub fn new(plans: Vec<Plan>) -> Self {
+        Self { plans }
+    }
+
+    pub fn plans(&self) -> &[Plan] {
+        &self.plans
+    }
+
+    pub fn plan(&self, tier: PlanTier) -> AppResult<&Plan> {
+        self.plans
+            .iter()
+            .find(|p| p.tier == tier)
+            .ok_or_else(|| AppError::Configuration(format!("Plan {} is not in the catalog", tier.as_str())))
+    }
+
+    // The first plan above `current` that would allow `count` units of the feature
+    pub fn upgrade_for(&self, current: PlanTier, feature: Feature, count: i64) -> Option<PlanTier> {
+        self.plans
+            .iter()
+            .skip_while(|p| p.tier != current)
+            .skip(1)
+            .find(|p| p.entitlement(feature).allows(count))
+            .map(|p| p.tier)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TenantPlan {
+    pub tenant_id: Uuid,
+    pub tier: PlanTier,
+    // Replace the plan's entitlement for the feature, whether more or less generous
+    #[serde(default)]
+    pub overrides: BTreeMap<Feature, Entitlement>,
+    pub updated_by: Option<Uuid>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FeatureStatus {
+    pub feature: Feature,
+    pub entitlement: Entitlement,
+    pub overridden: bool,
+    // Units held, for limit-type features
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub used: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TenantEntitlements {
+    pub tenant_id: Uuid,
+    pub tier: PlanTier,
+    pub features: Vec<FeatureStatus>,
+}
+
+// Why a gated feature was refused. Answered with 402 when a higher plan would allow it and 403
+// when none would (e.g. an override already below the plan's own limit on the top tier)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EntitlementDenied {
+    pub feature: Feature,
+    pub plan: PlanTier,
+    pub limit: Option<i64>,
+    pub used: Option<i64>,
+    pub upgrade_to: Option<PlanTier>,
+}
+
+impl fmt::Display for EntitlementDenied {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.limit, self.used) {
+            (Some(limit), Some(used)) => write!(
+                f,
+                "The {} plan allows {} {}, {} in use",
+                self.plan.as_str(),
+                limit,
+                self.feature.as_str(),
+                used
+            ),
+            _ => write!(f, "{} is not included in the {} plan", self.feature.as_str(), self.plan.as_str()),
+        }
+    }
+}
+
+#[async_trait]
+pub trait EntitlementStore: Send + Sync {
+    async fn tenant_plan(&self, tenant_id: Uuid) -> AppResult<Option<TenantPlan>>;
+    async fn set_tenant_plan(&self, plan: &TenantPlan) -> AppResult<()>;
+}
+
+// Plans are read from the store on every check, so a plan change applies to the next request
+pub struct EntitlementService {
+    store: Arc<dyn EntitlementStore>,
+    metering: Arc<MeteringService>,
+    catalog: PlanCatalog,
+    default_tier: PlanTier,
+}
+
+impl EntitlementService {
+    pub fn new(store: Arc<dyn EntitlementStore>, metering: Arc<MeteringService>) -> Self {
+        Self {
+            store,
+            metering,
+            catalog: PlanCatalog::default(),
+            default_tier: PlanTier::Free,
+        }
+    }
+
+    pub fn with_catalog(mut self, catalog: PlanCatalog) -> Self {
+        self.catalog = catalog;
+        self
+    }
+
+    // The plan of tenants that were never assigned one
+    pub fn with_default_tier(mut self, tier: PlanTier) -> Self {
+        self.default_tier = tier;
+        self
+    }
+
+    pub fn catalog(&self) -> &PlanCatalog {
+        &self.catalog
+    }
+
+    pub async fn tenant_plan(&self, tenant_id: Uuid) -> AppResult<TenantPlan> {
+        Ok(self.store.tenant_plan(tenant_id).await?.unwrap_or(TenantPlan {
+            tenant_id,
+            tier: self.default_tier,
+            overrides: BTreeMap::new(),
+            updated_by: None,
+            updated_at: None,
+        }))
+    }
+
+    pub async fn set_plan(
+        &self,
+        tenant_id: Uuid,
+        tier: PlanTier,
+        overrides: BTreeMap<Feature, Entitlement>,
+        actor: Uuid,
+        now: DateTime<Utc>,
+    ) -> AppResult<TenantPlan> {
+        self.catalog.plan(tier)?;
+        for (feature, entitlement) in &overrides {
+            match (feature.quota(), entitlement) {
+                (Some(_), Entitlement::Limit { limit }) if limit.map_or(true, |limit| limit >= 0) => {}
+                (Some(_), Entitlement::Limit { .. }) => {
+                    return Err(AppError::Validation(format!("Limit for {} must not be negative", feature.as_str())))
+                }
+                (None, Entitlement::Flag { .. }) => {}
+                _ => {
+                    return Err(AppError::Validation(format!(
+                        "Override for {} has the wrong type",
+                        feature.as_str()
+                    )))
+                }
+            }
+        }
+        let plan = TenantPlan { tenant_id, tier, overrides, updated_by: Some(actor), updated_at: Some(now) };
+        self.store.set_tenant_plan(&plan).await?;
+        info!("Tenant {} moved to the {} plan by {}", tenant_id, tier.as_str(), actor);
+        Ok(plan)
+    }
+
+    fn resolve(&self, plan: &TenantPlan, feature: Feature) -> AppResult<(Entitlement, bool)> {
+        match plan.overrides.get(&feature) {
+            Some(entitlement) => Ok((*entitlement, true)),
+            None => Ok((self.catalog.plan(plan.tier)?.entitlement(feature), false)),
+        }
+    }
+
+    fn denied(&self, plan: &TenantPlan, feature: Feature, entitlement: Entitlement, used: Option<i64>) -> AppError {
+        let needed = used.map_or(0, |used| used + 1);
+        AppError::EntitlementRequired(EntitlementDenied {
+            feature,
+            plan: plan.tier,
+            limit: entitlement.limit(),
+            used,
+            upgrade_to: self.catalog.upgrade_for(plan.tier, feature, needed),
+        })
+    }
+
+    pub async fn entitlements(&self, tenant_id: Uuid) -> AppResult<TenantEntitlements> {
+        let plan = self.tenant_plan(tenant_id).await?;
+        let mut features = Vec::with_capacity(Feature::ALL.len());
+        for &feature in Feature::ALL {
+            let (entitlement, overridden) = self.resolve(&plan, feature)?;
+            let used = match feature.quota() {
+                Some(quota) => Some(self.metering.used(tenant_id, quota).await?),
+                None => None,
+            };
+            features.push(FeatureStatus { feature, entitlement, overridden, used });
+        }
+        Ok(TenantEntitlements { tenant_id, tier: plan.tier, features })
+    }
+
+    // Fails with `EntitlementRequired` unless the tenant may use the feature, or for limits take
+    // one more unit of it. Doesn't count anything; entry points that add a unit use `acquire`.
+    pub async fn check_entitlement(&self, tenant_id: Uuid, feature: Feature) -> AppResult<()> {
+        let plan = self.tenant_plan(tenant_id).await?;
+        let (entitlement, _) = self.resolve(&plan, feature)?;
+        let used = match feature.quota() {
+            Some(quota) => Some(self.metering.used(tenant_id, quota).await?),
+            None => None,
+        };
+        if entitlement.allows(used.map_or(0, |used| used + 1)) {
+            Ok(())
+        } else {
+            Err(self.denied(&plan, feature, entitlement, used))
+        }
+    }
+
+    // Takes one unit of a limit-type feature for `holder`, idempotently. Returns whether a new
+    // unit was taken, i.e. whether a failed create should release it.
+    pub async fn acquire(&self, tenant_id: Uuid, feature: Feature, holder: &str) -> AppResult<bool> {
+        let Some(quota) = feature.quota() else {
+            return self.check_entitlement(tenant_id, feature).await.map(|_| false);
+        };
+        let plan = self.tenant_plan(tenant_id).await?;
+        let (entitlement, _) = self.resolve(&plan, feature)?;
+        // Unlimited plans still count, so a later downgrade sees what the tenant holds
+        let limit = entitlement.limit().unwrap_or(i64::MAX);
+        match self.metering.acquire_within(tenant_id, quota, Some(holder), limit).await? {
+            Acquire::Granted { .. } => Ok(true),
+            Acquire::AlreadyHeld => Ok(false),
+            Acquire::Exceeded { used } => Err(self.denied(&plan, feature, entitlement, Some(used))),
+        }
+    }
+
+    pub async fn release(&self, tenant_id: Uuid, feature: Feature, holder: &str) -> AppResult<()> {
+        match feature.quota() {
+            Some(quota) => self.metering.release(tenant_id, quota, Some(holder)).await,
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metering::InMemoryUsageStore;
+    use axum::{http::StatusCode, response::IntoResponse};
+    use serde_json::{json, Value};
+
+    fn service() -> EntitlementService {
+        let metering = Arc::new(MeteringService::new(Arc::new(InMemoryUsageStore::new())));
+        EntitlementService::new(Arc::new(InMemoryEntitlementStore::new()), metering)
+    }
+
+    fn denial(result: AppResult<impl fmt::Debug>) -> EntitlementDenied {
+        match result {
+            Err(AppError::EntitlementRequired(denied)) => denied,
+            other => panic!("expected an entitlement denial, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_limit_enforced_at_boundary() {
+        let entitlements = service();
+        let tenant = Uuid::new_v4();
+
+        entitlements.check_entitlement(tenant, Feature::CloudAccounts).await.unwrap();
+        assert!(entitlements.acquire(tenant, Feature::CloudAccounts, "aws/111").await.unwrap());
+        // Reporting the same account again doesn't count twice
+        assert!(!entitlements.acquire(tenant, Feature::CloudAccounts, "aws/111").await.unwrap());
+
+        let denied = denial(entitlements.acquire(tenant, Feature::CloudAccounts, "aws/222").await);
+        assert_eq!((denied.limit, denied.used, denied.upgrade_to), (Some(1), Some(1), Some(PlanTier::Pro)));
+        denial(entitlements.check_entitlement(tenant, Feature::CloudAccounts).await);
+
+        entitlements.release(tenant, Feature::CloudAccounts, "aws/111").await.unwrap();
+        assert!(entitlements.acquire(tenant, Feature::CloudAccounts, "aws/222").await.unwrap());
+
+        // Upgrading applies to the very next check
+        entitlements.set_plan(tenant, PlanTier::Pro, BTreeMap::new(), Uuid::new_v4(), Utc::now()).await.unwrap();
+        assert!(entitlements.acquire(tenant, Feature::CloudAccounts, "aws/333").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_overrides_beat_plan_defaults() {
+        let entitlements = service();
+        let tenant = Uuid::new_v4();
+        let admin = Uuid::new_v4();
+        let overrides = BTreeMap::from([
+            (Feature::AutoMl, Entitlement::Flag { enabled: true }),
+            (Feature::SsoProviders, Entitlement::Limit { limit: Some(0) }),
+        ]);
+        entitlements.set_plan(tenant, PlanTier::Pro, overrides, admin, Utc::now()).await.unwrap();
+
+        entitlements.check_entitlement(tenant, Feature::AutoMl).await.unwrap();
+        // Pro includes one SSO provider, but the override takes it away
+        let denied = denial(entitlements.acquire(tenant, Feature::SsoProviders, "okta").await);
+        assert_eq!((denied.limit, denied.upgrade_to), (Some(0), Some(PlanTier::Enterprise)));
+        denial(entitlements.check_entitlement(tenant, Feature::MeshManagement).await);
+
+        let status = entitlements.entitlements(tenant).await.unwrap();
+        let sso = status.features.iter().find(|f| f.feature == Feature::SsoProviders).unwrap();
+        assert!(sso.overridden);
+        assert_eq!(sso.used, Some(0));
+
+        let wrong_type = BTreeMap::from([(Feature::AutoMl, Entitlement::Limit { limit: Some(1) })]);
+        let result = entitlements.set_plan(tenant, PlanTier::Pro, wrong_type, admin, Utc::now()).await;
+        assert!(matches!(result, Err(AppError::Validation(_))));
+    }
+
+    #[tokio::test]
+    async fn test_denial_payload() {
+        let entitlements = service();
+        let tenant = Uuid::new_v4();
+
+        let response = entitlements.check_entitlement(tenant, Feature::MeshManagement).await.unwrap_err().into_response();
+        assert_eq!(response.status(), StatusCode::PAYMENT_REQUIRED);
+        let body: Value = serde_json::from_slice(&hyper::body::to_bytes(response.into_body()).await.unwrap()).unwrap();
+        assert_eq!(
+            body,
+            json!({
+                "error": "mesh_management is not included in the free plan",
+                "feature": "mesh_management",
+                "plan": "free",
+                "limit": null,
+                "used": null,
+                "upgrade_to": "enterprise",
+            })
+        );
+
+        // Nothing above enterprise, so a capped enterprise tenant is refused outright
+        let capped = BTreeMap::from([(Feature::CloudAccounts, Entitlement::Limit { limit: Some(0) })]);
+        entitlements.set_plan(tenant, PlanTier::Enterprise, capped, Uuid::new_v4(), Utc::now()).await.unwrap();
+        let response = entitlements.acquire(tenant, Feature::CloudAccounts, "gcp/p1").await.unwrap_err().into_response();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let body: Value = serde_json::from_slice(&hyper::body::to_bytes(response.into_body()).await.unwrap()).unwrap();
+        assert_eq!(body["limit"], 0);
+        assert_eq!(body["used"], 0);
+        assert_eq!(body["upgrade_to"], Value::Null);
+    }
+}
@@ -0,0 +1,82 @@
+use std::collections::{BTreeMap, HashMap};
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::types::Json;
+use sqlx::PgPool; // CockroachDB uses PostgreSQL protocol
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use super::{Entitlement, EntitlementStore, Feature, PlanTier, TenantPlan};
+use crate::error::{AppError, AppResult};
+
+pub struct PgEntitlementStore {
+    pool: PgPool,
+}
+
+impl PgEntitlementStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl EntitlementStore for PgEntitlementStore {
+    async fn tenant_plan(&self, tenant_id: Uuid) -> AppResult<Option<TenantPlan>> {
+        let row: Option<(PlanTier, Json<BTreeMap<Feature, Entitlement>>, Option<Uuid>, DateTime<Utc>)> = sqlx::query_as(
+            "SELECT tier, overrides, updated_by, updated_at FROM tenant_plans WHERE tenant_id = $1",
+        )
+        .bind(tenant_id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(row.map(|(tier, overrides, updated_by, updated_at)| TenantPlan {
+            tenant_id,
+            tier,
+            overrides: overrides.0,
+            updated_by,
+            updated_at: Some(updated_at),
+        }))
+    }
+
+    async fn set_tenant_plan(&self, plan: &TenantPlan) -> AppResult<()> {
+        sqlx::query(
+            r#"UPSERT INTO tenant_plans (tenant_id, tier, overrides, updated_by, updated_at)
+            VALUES ($1, $2, $3, $4, COALESCE($5, CURRENT_TIMESTAMP))"#
+        )
+        .bind(plan.tenant_id)
+        .bind(plan.tier)
+        .bind(Json(&plan.overrides))
+        .bind(plan.updated_by)
+        .bind(plan.updated_at)
+        .execute(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryEntitlementStore {
+    plans: Mutex<HashMap<Uuid, TenantPlan>>,
+}
+
+impl InMemoryEntitlementStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl EntitlementStore for InMemoryEntitlementStore {
+    async fn tenant_plan(&self, tenant_id: Uuid) -> AppResult<Option<TenantPlan>> {
+        Ok(self.plans.lock().await.get(&tenant_id).cloned())
+    }
+
+    async fn set_tenant_plan(&self, plan: &TenantPlan) -> AppResult<()> {
+        self.plans.lock().await.insert(plan.tenant_id, plan.clone());
+        Ok(())
+    }
+}
@@ -1,8 +1,11 @@
-use axum::{http::StatusCode, response::{IntoResponse, Response}, Json};
+use axum::{http::{header::RETRY_AFTER, StatusCode}, response::{IntoResponse, Response}, Json};
 use serde_json::json;
+use sirsi_common::{ErrorKind, Retryable};
 use thiserror::Error;
 use validator::ValidationErrors;
 
+use crate::entitlements::EntitlementDenied;
+
 #[derive(Debug, Error)]
 pub enum Error {
     #[error("Database error: {0}")]
@@ -11,6 +14,27 @@ pub enum Error {
     #[error("Authentication error: {0}")]
     Auth(String),
     
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
+    #[error("Conflict: {message}")]
+    Conflict { message: String, current: Option<serde_json::Value> },
+
+    #[error("Quota {quota} exceeded: {used} of {limit} in use")]
+    QuotaExceeded { quota: String, limit: i64, used: i64 },
+
+    #[error("{0}")]
+    EntitlementRequired(EntitlementDenied),
+
+    #[error("Rate limited: {message}")]
+    RateLimited { message: String, retry_after_secs: u64 },
+
+    #[error("Precondition required: {0}")]
+    PreconditionRequired(String),
+
+    #[error("Payload too large: {0}")]
+    PayloadTooLarge(String),
+
     #[error("Invalid input: {0}")]
     InvalidInput(String),
     
@@ -37,13 +61,88 @@ pub enum Error {
     
     #[error("Server error: {0}")]
     Server(String),
+
+    #[error("Provider error ({kind}): {message}")]
+    Provider { kind: ErrorKind, message: String },
+}
+
+impl Error {
+    /// Wraps an error from one of the provider crates, preserving its classification.
+    pub fn from_provider<E: Retryable + std::fmt::Display>(error: E) -> Self {
+        Error::Provider {
+            kind: error.kind(),
+            message: error.to_string(),
+        }
+    }
+}
+
+impl Retryable for Error {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Provider { kind, .. } => *kind,
+            Error::Auth(_) | Error::Forbidden(_) | Error::QuotaExceeded { .. } | Error::EntitlementRequired(_) => {
+                ErrorKind::AuthFailure
+            }
+            Error::InvalidInput(_)
+            | Error::Validation(_)
+            | Error::Serialization(_)
+            | Error::PayloadTooLarge(_)
+            | Error::PreconditionRequired(_) => ErrorKind::InvalidInput,
+            Error::Conflict { .. } => ErrorKind::Conflict,
+            Error::RateLimited { .. } => ErrorKind::Throttled,
+            Error::NotFound(_) => ErrorKind::NotFound,
+            Error::Connection(_) => ErrorKind::ProviderOutage,
+            Error::Database(sqlx::Error::PoolTimedOut) => ErrorKind::ProviderOutage,
+            _ => ErrorKind::Internal,
+        }
+    }
 }
 
 impl IntoResponse for Error {
     fn into_response(self) -> Response {
         let (status, error_message) = match self {
             Error::Auth(msg) => (StatusCode::UNAUTHORIZED, msg),
+            Error::Forbidden(msg) => (StatusCode::FORBIDDEN, msg),
             Error::Database(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)),
+            Error::PayloadTooLarge(msg) => (StatusCode::PAYLOAD_TOO_LARGE, msg),
+            Error::PreconditionRequired(msg) => (StatusCode::PRECONDITION_REQUIRED, msg),
+            Error::QuotaExceeded { quota, limit, used } => {
+                let body = Json(json!({
+                    "error": format!("Quota {} exceeded", quota),
+                    "quota": quota,
+                    "limit": limit,
+                    "used": used,
+                }));
+                return (StatusCode::FORBIDDEN, body).into_response();
+            }
+            // 402 when a higher plan would allow it, naming that plan
+            Error::EntitlementRequired(denied) => {
+                let status = match denied.upgrade_to {
+                    Some(_) => StatusCode::PAYMENT_REQUIRED,
+                    None => StatusCode::FORBIDDEN,
+                };
+                let body = Json(json!({
+                    "error": denied.to_string(),
+                    "feature": denied.feature,
+                    "plan": denied.plan,
+                    "limit": denied.limit,
+                    "used": denied.used,
+                    "upgrade_to": denied.upgrade_to,
+                }));
+                return (status, body).into_response();
+            }
+            Error::RateLimited { message, retry_after_secs } => {
+                let body = Json(json!({ "error": message, "retry_after": retry_after_secs }));
+                return (StatusCode::TOO_MANY_REQUESTS, [(RETRY_AFTER, retry_after_secs.to_string())], body).into_response();
+            }
+            Error::Conflict { message, current } => {
+                let body = Json(json!({
+                    "error": message,
+                    "kind": ErrorKind::Conflict,
+                    "current": current,
+                }));
+                return (StatusCode::CONFLICT, body).into_response();
+            }
             Error::InvalidInput(msg) => (StatusCode::BAD_REQUEST, msg),
             Error::NotFound(msg) => (StatusCode::NOT_FOUND, msg),
             Error::Internal(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
@@ -53,6 +152,15 @@ impl IntoResponse for Error {
             Error::Serialization(msg) => (StatusCode::BAD_REQUEST, msg),
             Error::ExternalService(msg) => (StatusCode::BAD_GATEWAY, msg),
             Error::Server(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
+            Error::Provider { kind, message } => {
+                let status = StatusCode::from_u16(kind.http_status()).unwrap_or(StatusCode::BAD_GATEWAY);
+                let body = Json(json!({
+                    "error": message,
+                    "kind": kind,
+                    "retryable": kind.is_retryable(),
+                }));
+                return (status, body).into_response();
+            }
         };
 
         let body = Json(json!({
@@ -91,3 +199,29 @@ pub type Result<T> = std::result::Result<T, Error>;
 // Type aliases for backward compatibility
 pub type AppError = Error;
 pub type AppResult<T> = Result<T>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn provider_status(kind: ErrorKind) -> StatusCode {
+        Error::Provider { kind, message: "upstream failure".into() }
+            .into_response()
+            .status()
+    }
+
+    #[test]
+    fn test_provider_kind_selects_status() {
+        assert_eq!(provider_status(ErrorKind::Throttled), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(provider_status(ErrorKind::ProviderOutage), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(provider_status(ErrorKind::Internal), StatusCode::BAD_GATEWAY);
+        assert_eq!(provider_status(ErrorKind::NotFound), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_from_provider_preserves_kind() {
+        let err = Error::from_provider(ErrorKind::Throttled);
+        assert!(err.is_retryable());
+        assert_eq!(err.kind(), ErrorKind::Throttled);
+    }
+}
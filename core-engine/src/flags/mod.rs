@@ -0,0 +1,639 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration as StdDuration;
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use crate::error::{AppError, AppResult};
+use crate::models::version::{settle_update, Versioned};
+
+pub mod store;
+
+pub use store::{InMemoryFlagStore, PgFlagStore};
+
+// Rollouts bucket into 0.01% steps
+const BUCKETS: u64 = 10_000;
+const CHANGE_BUFFER: usize = 256;
+pub const DEFAULT_SYNC_INTERVAL: StdDuration = StdDuration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "VARCHAR", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum FlagKind {
+    Boolean,
+    Percentage,
+    Variant,
+}
+
+// What percentage rollouts and splits hash, so a subject stays in the same bucket
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "VARCHAR", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum BucketBy {
+    #[default]
+    Subject,
+    Tenant,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VariantWeight {
+    pub name: String,
+    pub weight: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Serve {
+    On,
+    Off,
+    Rollout { percent: f64 },
+    Variant { name: String },
+    Split { weights: Vec<VariantWeight> },
+}
+
+// Same shape as network policy selectors: every label and every expression must match, and
+// an empty selector matches everyone
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ContextSelector {
+    #[serde(default)]
+    pub match_labels: HashMap<String, String>,
+    #[serde(default)]
+    pub match_expressions: Vec<SelectorExpression>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SelectorExpression {
+    pub key: String,
+    pub operator: SelectorOperator,
+    #[serde(default)]
+    pub values: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SelectorOperator {
+    In,
+    NotIn,
+    Exists,
+    DoesNotExist,
+}
+
+impl ContextSelector {
+    pub fn matches(&self, context: &EvaluationContext) -> bool {
+        self.match_labels
+            .iter()
+            .all(|(key, value)| context.value(key).as_deref() == Some(value.as_str()))
+            && self.match_expressions.iter().all(|expression| {
+                let value = context.value(&expression.key);
+                match expression.operator {
+                    SelectorOperator::In => value.is_some_and(|v| expression.values.contains(&v)),
+                    SelectorOperator::NotIn => value.is_none_or(|v| !expression.values.contains(&v)),
+                    SelectorOperator::Exists => value.is_some(),
+                    SelectorOperator::DoesNotExist => value.is_none(),
+                }
+            })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TargetingRule {
+    #[serde(default)]
+    pub description: Option<String>,
+    pub selector: ContextSelector,
+    pub serve: Serve,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FlagDefinition {
+    pub key: String,
+    #[serde(default)]
+    pub description: String,
+    pub kind: FlagKind,
+    // Off serves nothing to anyone, whatever the rules say
+    pub enabled: bool,
+    #[serde(default)]
+    pub bucket_by: BucketBy,
+    // Evaluated in order; the first matching rule wins and `fallthrough` serves everyone else
+    #[serde(default)]
+    pub rules: Vec<TargetingRule>,
+    pub fallthrough: Serve,
+}
+
+impl FlagDefinition {
+    pub fn validate(&self) -> AppResult<()> {
+        let valid_key = !self.key.is_empty()
+            && self.key.len() <= 128
+            && self.key.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        if !valid_key {
+            return Err(AppError::Validation(format!(
+                "Flag key '{}' must be 1-128 letters, digits, '-', '_' or '.'",
+                self.key
+            )));
+        }
+        for serve in self.rules.iter().map(|r| &r.serve).chain(std::iter::once(&self.fallthrough)) {
+            let allowed = match (self.kind, serve) {
+                (_, Serve::Off) => true,
+                (FlagKind::Boolean | FlagKind::Percentage, Serve::On) => true,
+                (FlagKind::Percentage, Serve::Rollout { percent }) => (0.0..=100.0).contains(percent),
+                (FlagKind::Variant, Serve::Variant { name }) => !name.is_empty(),
+                (FlagKind::Variant, Serve::Split { weights }) => {
+                    !weights.is_empty() && weights.iter().map(|w| u64::from(w.weight)).sum::<u64>() > 0
+                }
+                _ => false,
+            };
+            if !allowed {
+                return Err(AppError::Validation(format!(
+                    "{:?} flag '{}' cannot serve {:?}",
+                    self.kind, self.key, serve
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeatureFlag {
+    pub id: Uuid,
+    #[serde(flatten)]
+    pub definition: FlagDefinition,
+    pub version: i64,
+    pub updated_by: Option<Uuid>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Versioned for FeatureFlag {
+    fn version(&self) -> i64 {
+        self.version
+    }
+}
+
+// Who a flag is evaluated for. Selectors can address `subject`, `tenant_id`, `role` or any
+// attribute by name.
+#[derive(Debug, Clone, Default)]
+pub struct EvaluationContext {
+    pub subject: Option<String>,
+    pub tenant_id: Option<Uuid>,
+    pub role: Option<String>,
+    pub attributes: HashMap<String, String>,
+}
+
+impl EvaluationContext {
+    pub fn new(subject: impl Into<String>) -> Self {
+        Self { subject: Some(subject.into()), ..Self::default() }
+    }
+
+    pub fn with_tenant(mut self, tenant_id: Uuid) -> Self {
+        self.tenant_id = Some(tenant_id);
+        self
+    }
+
+    pub fn with_role(mut self, role: impl Into<String>) -> Self {
+        self.role = Some(role.into());
+        self
+    }
+
+    pub fn with_attribute(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.attributes.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn value(&self, key: &str) -> Option<String> {
+        match key {
+            "subject" => self.subject.clone(),
+            "tenant_id" => self.tenant_id.map(|t| t.to_string()),
+            "role" => self.role.clone(),
+            _ => self.attributes.get(key).cloned(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EvaluationReason {
+    FlagNotFound,
+    Disabled,
+    Rule { index: usize },
+    Fallthrough,
+    // A rollout or split needs a subject (or tenant) to bucket
+    NoBucketingKey,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Evaluation {
+    pub enabled: bool,
+    pub variant: Option<String>,
+    pub reason: EvaluationReason,
+}
+
+impl Evaluation {
+    fn off(reason: EvaluationReason) -> Self {
+        Self { enabled: false, variant: None, reason }
+    }
+}
+
+// Stable 0..BUCKETS position for a subject, independent per flag so rollouts of different
+// flags don't always pick the same subjects
+pub fn bucket(flag_key: &str, bucketing_key: &str) -> u64 {
+    let digest = Sha256::digest(format!("{}:{}", flag_key, bucketing_key).as_bytes());
+    let mut prefix = [0u8; 8];
+    prefix.copy_from_slice(&digest[..8]);
+    u64::from_be_bytes(prefix) % BUCKETS
+}
+
+pub fn evaluate(flag: &FeatureFlag, context: &EvaluationContext) -> Evaluation {
+    let definition = &flag.definition;
+    if !definition.enabled {
+        return Evaluation::off(EvaluationReason::Disabled);
+    }
+    let (serve, reason) = definition
+        .rules
+        .iter()
+        .enumerate()
+        .find(|(_, rule)| rule.selector.matches(context))
+        .map(|(index, rule)| (&rule.serve, EvaluationReason::Rule { index }))
+        .unwrap_or((&definition.fallthrough, EvaluationReason::Fallthrough));
+
+    let bucketing_key = match definition.bucket_by {
+        BucketBy::Subject => context.subject.clone(),
+        BucketBy::Tenant => context.tenant_id.map(|t| t.to_string()),
+    };
+    let position = bucketing_key.map(|key| bucket(&definition.key, &key));
+    match serve {
+        Serve::On => Evaluation { enabled: true, variant: None, reason },
+        Serve::Off => Evaluation::off(reason),
+        Serve::Variant { name } => Evaluation { enabled: true, variant: Some(name.clone()), reason },
+        Serve::Rollout { percent } => match position {
+            None => Evaluation::off(EvaluationReason::NoBucketingKey),
+            Some(position) => Evaluation {
+                enabled: (position as f64) < percent * (BUCKETS as f64) / 100.0,
+                variant: None,
+                reason,
+            },
+        },
+        Serve::Split { weights } => {
+            let Some(position) = position else {
+                return Evaluation::off(EvaluationReason::NoBucketingKey);
+            };
+            let total: u64 = weights.iter().map(|w| u64::from(w.weight)).sum();
+            let target = position * total / BUCKETS;
+            let mut cumulative = 0;
+            let variant = weights.iter().find(|w| {
+                cumulative += u64::from(w.weight);
+                target < cumulative
+            });
+            Evaluation {
+                enabled: variant.is_some(),
+                variant: variant.map(|w| w.name.clone()),
+                reason,
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum FlagChange {
+    Upserted(Box<FeatureFlag>),
+    Deleted { key: String },
+}
+
+#[async_trait]
+pub trait FlagStore: Send + Sync {
+    async fn list(&self) -> AppResult<Vec<FeatureFlag>>;
+    async fn get(&self, key: &str) -> AppResult<Option<FeatureFlag>>;
+    // False when the key is taken
+    async fn create(&self, flag: &FeatureFlag) -> AppResult<bool>;
+    // Applies only while the stored version is still `flag.version`; returns the new row
+    async fn update(&self, flag: &FeatureFlag) -> AppResult<Option<FeatureFlag>>;
+    async fn delete(&self, key: &str) -> AppResult<bool>;
+}
+
+pub struct FlagService {
+    store: Arc<dyn FlagStore>,
+    changes: broadcast::Sender<FlagChange>,
+}
+
+impl FlagService {
+    pub fn new(store: Arc<dyn FlagStore>) -> Self {
+        let (changes, _) = broadcast::channel(CHANGE_BUFFER);
+        Self { store, changes }
+    }
+
+    // Changes made through this service; other replicas pick theirs up on the next sync
+    pub fn subscribe(&self) -> broadcast::Receiver<FlagChange> {
+        self.changes.subscribe()
+    }
+
+    fn publish(&self, change: FlagChange) {
+        // No subscribers is fine; clients also resync on their interval
+        let _ = self.changes.send(change);
+    }
+
+    pub async fn list(&self) -> AppResult<Vec<FeatureFlag>> {
+        self.store.list().await
+    }
+
+    pub async fn get(&self, key: &str) -> AppResult<FeatureFlag> {
+        self.store
+            .get(key)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("Flag '{}' not found", key)))
+    }
+
+    pub async fn create(&self, definition: FlagDefinition, actor: Uuid, now: DateTime<Utc>) -> AppResult<FeatureFlag> {
+        definition.validate()?;
+        let flag = FeatureFlag {
+            id: Uuid::new_v4(),
+            definition,
+            version: 1,
+            updated_by: Some(actor),
+            updated_at: now,
+        };
+        if !self.store.create(&flag).await? {
+            return Err(AppError::Conflict {
+                message: format!("Flag '{}' already exists", flag.definition.key),
+                current: None,
+            });
+        }
+        self.publish(FlagChange::Upserted(Box::new(flag.clone())));
+        Ok(flag)
+    }
+
+    // Replaces the definition if the flag is still at `expected_version`
+    pub async fn update(
+        &self,
+        key: &str,
+        definition: FlagDefinition,
+        expected_version: i64,
+        actor: Uuid,
+        now: DateTime<Utc>,
+    ) -> AppResult<FeatureFlag> {
+        definition.validate()?;
+        if definition.key != key {
+            return Err(AppError::Validation("Flag keys cannot be changed".into()));
+        }
+        let current = self.get(key).await?;
+        let flag = FeatureFlag {
+            id: current.id,
+            definition,
+            version: expected_version,
+            updated_by: Some(actor),
+            updated_at: now,
+        };
+        let updated = self.store.update(&flag).await?;
+        let flag = settle_update("Flag", updated, self.store.get(key)).await?;
+        self.publish(FlagChange::Upserted(Box::new(flag.clone())));
+        Ok(flag)
+    }
+
+    pub async fn delete(&self, key: &str) -> AppResult<()> {
+        if !self.store.delete(key).await? {
+            return Err(AppError::NotFound(format!("Flag '{}' not found", key)));
+        }
+        self.publish(FlagChange::Deleted { key: key.to_string() });
+        Ok(())
+    }
+}
+
+// In-process evaluation against a local copy of every flag. The copy follows the service's
+// change stream and is reloaded every sync interval, so admin changes land within seconds
+// and evaluations never wait on the database.
+pub struct FlagClient {
+    service: Arc<FlagService>,
+    flags: RwLock<HashMap<String, FeatureFlag>>,
+}
+
+impl FlagClient {
+    pub fn new(service: Arc<FlagService>) -> Self {
+        Self { service, flags: RwLock::new(HashMap::new()) }
+    }
+
+    pub async fn refresh(&self) -> AppResult<()> {
+        let flags = self.service.list().await?;
+        let mut cached = self.flags.write().unwrap_or_else(|e| e.into_inner());
+        *cached = flags.into_iter().map(|f| (f.definition.key.clone(), f)).collect();
+        Ok(())
+    }
+
+    pub fn apply(&self, change: FlagChange) {
+        let mut cached = self.flags.write().unwrap_or_else(|e| e.into_inner());
+        match change {
+            FlagChange::Upserted(flag) => {
+                // A resync may already hold a newer version than a buffered change
+                let newer = cached.get(&flag.definition.key).is_some_and(|c| c.version > flag.version);
+                if !newer {
+                    cached.insert(flag.definition.key.clone(), *flag);
+                }
+            }
+            FlagChange::Deleted { key } => {
+                cached.remove(&key);
+            }
+        }
+    }
+
+    pub fn evaluate(&self, key: &str, context: &EvaluationContext) -> Evaluation {
+        let cached = self.flags.read().unwrap_or_else(|e| e.into_inner());
+        match cached.get(key) {
+            Some(flag) => evaluate(flag, context),
+            None => Evaluation::off(EvaluationReason::FlagNotFound),
+        }
+    }
+
+    // Unknown flags are off
+    pub fn is_enabled(&self, key: &str, context: &EvaluationContext) -> bool {
+        self.evaluate(key, context).enabled
+    }
+
+    pub fn variant(&self, key: &str, context: &EvaluationContext) -> Option<String> {
+        self.evaluate(key, context).variant
+    }
+
+    pub fn spawn_sync(self: Arc<Self>, interval: StdDuration) -> JoinHandle<()> {
+        let mut changes = self.service.subscribe();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                tokio::select! {
+                    change = changes.recv() => match change {
+                        Ok(change) => self.apply(change),
+                        Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                            debug!("Flag client missed {} changes; reloading", skipped);
+                            if let Err(e) = self.refresh().await {
+                                warn!("Failed to reload feature flags: {}", e);
+                            }
+                        }
+                        Err(broadcast::error::RecvError::Closed) => return,
+                    },
+                    _ = ticker.tick() => {
+                        if let Err(e) = self.refresh().await {
+                            warn!("Failed to reload feature flags: {}", e);
+                        }
+                    }
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn definition(key: &str, kind: FlagKind, rules: Vec<TargetingRule>, fallthrough: Serve) -> FlagDefinition {
+        FlagDefinition {
+            key: key.to_string(),
+            description: String::new(),
+            kind,
+            enabled: true,
+            bucket_by: BucketBy::Subject,
+            rules,
+            fallthrough,
+        }
+    }
+
+    fn flag(definition: FlagDefinition) -> FeatureFlag {
+        FeatureFlag { id: Uuid::new_v4(), definition, version: 1, updated_by: None, updated_at: Utc::now() }
+    }
+
+    fn rule(selector: ContextSelector, serve: Serve) -> TargetingRule {
+        TargetingRule { description: None, selector, serve }
+    }
+
+    #[test]
+    fn test_rollout_bucketing_is_sticky() {
+        let rollout = |percent| flag(definition("new-billing", FlagKind::Percentage, vec![], Serve::Rollout { percent }));
+        let subjects: Vec<String> = (0..10_000).map(|i| format!("user-{}", i)).collect();
+        let enabled_at = |percent| -> Vec<bool> {
+            let flag = rollout(percent);
+            subjects.iter().map(|s| evaluate(&flag, &EvaluationContext::new(s.as_str())).enabled).collect()
+        };
+
+        let ten = enabled_at(10.0);
+        assert_eq!(ten, enabled_at(10.0));
+        let share = ten.iter().filter(|e| **e).count();
+        assert!((900..1100).contains(&share), "{} of 10000 enabled at 10%", share);
+        // Growing the rollout only adds subjects
+        let fifty = enabled_at(50.0);
+        assert!(ten.iter().zip(&fifty).all(|(at_ten, at_fifty)| !at_ten || *at_fifty));
+        assert!(enabled_at(100.0).iter().all(|e| *e));
+        assert!(enabled_at(0.0).iter().all(|e| !*e));
+        // Buckets are per flag, so another flag's 10% is a different 10%
+        assert_ne!(bucket("new-billing", "user-1"), bucket("dark-mode", "user-1"));
+        assert_eq!(bucket("new-billing", "user-1"), bucket("new-billing", "user-1"));
+        assert_eq!(evaluate(&rollout(50.0), &EvaluationContext::default()).reason, EvaluationReason::NoBucketingKey);
+
+        let split = flag(definition(
+            "checkout",
+            FlagKind::Variant,
+            vec![],
+            Serve::Split {
+                weights: vec![
+                    VariantWeight { name: "control".into(), weight: 1 },
+                    VariantWeight { name: "treatment".into(), weight: 1 },
+                ],
+            },
+        ));
+        let variants: Vec<_> = subjects.iter().take(200).map(|s| evaluate(&split, &EvaluationContext::new(s.as_str())).variant).collect();
+        let again: Vec<_> = subjects.iter().take(200).map(|s| evaluate(&split, &EvaluationContext::new(s.as_str())).variant).collect();
+        assert_eq!(variants, again);
+        assert!(variants.iter().any(|v| v.as_deref() == Some("control")));
+        assert!(variants.iter().any(|v| v.as_deref() == Some("treatment")));
+    }
+
+    #[test]
+    fn test_first_matching_rule_wins() {
+        let tenant = Uuid::new_v4();
+        let admins = ContextSelector {
+            match_labels: HashMap::from([("role".to_string(), "admin".to_string())]),
+            ..ContextSelector::default()
+        };
+        let beta_tenants = ContextSelector {
+            match_expressions: vec![SelectorExpression {
+                key: "tenant_id".into(),
+                operator: SelectorOperator::In,
+                values: vec![tenant.to_string()],
+            }],
+            ..ContextSelector::default()
+        };
+        let free_plan = ContextSelector {
+            match_expressions: vec![SelectorExpression { key: "plan".into(), operator: SelectorOperator::NotIn, values: vec!["enterprise".into()] }],
+            ..ContextSelector::default()
+        };
+        let mut exports = flag(definition(
+            "bulk-export",
+            FlagKind::Boolean,
+            vec![rule(admins, Serve::Off), rule(beta_tenants, Serve::On), rule(free_plan, Serve::Off)],
+            Serve::On,
+        ));
+
+        let beta_admin = EvaluationContext::new("u1").with_tenant(tenant).with_role("admin");
+        assert_eq!(evaluate(&exports, &beta_admin), Evaluation { enabled: false, variant: None, reason: EvaluationReason::Rule { index: 0 } });
+        let beta_user = EvaluationContext::new("u2").with_tenant(tenant).with_role("user");
+        assert_eq!(evaluate(&exports, &beta_user).reason, EvaluationReason::Rule { index: 1 });
+        assert!(evaluate(&exports, &beta_user).enabled);
+        // No plan attribute counts as "not enterprise"
+        let other = EvaluationContext::new("u3").with_tenant(Uuid::new_v4());
+        assert_eq!(evaluate(&exports, &other).reason, EvaluationReason::Rule { index: 2 });
+        let enterprise = other.clone().with_attribute("plan", "enterprise");
+        assert_eq!(evaluate(&exports, &enterprise), Evaluation { enabled: true, variant: None, reason: EvaluationReason::Fallthrough });
+
+        exports.definition.enabled = false;
+        assert_eq!(evaluate(&exports, &beta_user).reason, EvaluationReason::Disabled);
+
+        let invalid = definition("bulk-export", FlagKind::Boolean, vec![], Serve::Rollout { percent: 5.0 });
+        assert!(matches!(invalid.validate(), Err(AppError::Validation(_))));
+        assert!(definition("bad key!", FlagKind::Boolean, vec![], Serve::On).validate().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_client_cache_follows_admin_changes() {
+        let service = Arc::new(FlagService::new(Arc::new(InMemoryFlagStore::new())));
+        let admin = Uuid::new_v4();
+        let created = service
+            .create(definition("dark-mode", FlagKind::Boolean, vec![], Serve::Off), admin, Utc::now())
+            .await
+            .unwrap();
+        let client = Arc::new(FlagClient::new(service.clone()));
+        client.refresh().await.unwrap();
+        let context = EvaluationContext::new("u1");
+        assert!(!client.is_enabled("dark-mode", &context));
+        assert_eq!(client.evaluate("unknown", &context).reason, EvaluationReason::FlagNotFound);
+
+        // A long interval, so only the change stream can deliver the update in time
+        let sync = client.clone().spawn_sync(StdDuration::from_secs(3600));
+        tokio::time::sleep(StdDuration::from_millis(20)).await;
+        let updated = service
+            .update("dark-mode", definition("dark-mode", FlagKind::Boolean, vec![], Serve::On), created.version, admin, Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(updated.version, 2);
+        let deadline = tokio::time::Instant::now() + StdDuration::from_secs(2);
+        while !client.is_enabled("dark-mode", &context) {
+            assert!(tokio::time::Instant::now() < deadline, "update never reached the client");
+            tokio::time::sleep(StdDuration::from_millis(5)).await;
+        }
+
+        // Stale writers get a conflict carrying the current flag, and nothing is published
+        let stale = service
+            .update("dark-mode", definition("dark-mode", FlagKind::Boolean, vec![], Serve::Off), created.version, admin, Utc::now())
+            .await;
+        assert!(matches!(stale, Err(AppError::Conflict { current: Some(_), .. })));
+        // An out-of-order change never rolls the cache back
+        client.apply(FlagChange::Upserted(Box::new(created)));
+        assert!(client.is_enabled("dark-mode", &context));
+
+        service.delete("dark-mode").await.unwrap();
+        let deadline = tokio::time::Instant::now() + StdDuration::from_secs(2);
+        while client.evaluate("dark-mode", &context).reason != EvaluationReason::FlagNotFound {
+            assert!(tokio::time::Instant::now() < deadline, "delete never reached the client");
+            tokio::time::sleep(StdDuration::from_millis(5)).await;
+        }
+        sync.abort();
+    }
+}
@@ -0,0 +1,191 @@
+use std::collections::HashMap;
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::types::Json;
+use sqlx::PgPool; // CockroachDB uses PostgreSQL protocol
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use super::{BucketBy, FeatureFlag, FlagDefinition, FlagKind, FlagStore, Serve, TargetingRule};
+use crate::error::{AppError, AppResult};
+
+#[derive(sqlx::FromRow)]
+struct FlagRow {
+    id: Uuid,
+    key: String,
+    description: String,
+    kind: FlagKind,
+    enabled: bool,
+    bucket_by: BucketBy,
+    rules: Json<Vec<TargetingRule>>,
+    fallthrough: Json<Serve>,
+    version: i64,
+    updated_by: Option<Uuid>,
+    updated_at: DateTime<Utc>,
+}
+
+impl From<FlagRow> for FeatureFlag {
+    fn from(row: FlagRow) -> Self {
+        Self {
+            id: row.id,
+            definition: FlagDefinition {
+                key: row.key,
+                description: row.description,
+                kind: row.kind,
+                enabled: row.enabled,
+                bucket_by: row.bucket_by,
+                rules: row.rules.0,
+                fallthrough: row.fallthrough.0,
+            },
+            version: row.version,
+            updated_by: row.updated_by,
+            updated_at: row.updated_at,
+        }
+    }
+}
+
+const FLAG_COLUMNS: &str =
+    "id, key, description, kind, enabled, bucket_by, rules, fallthrough, version, updated_by, updated_at";
+
+pub struct PgFlagStore {
+    pool: PgPool,
+}
+
+impl PgFlagStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl FlagStore for PgFlagStore {
+    async fn list(&self) -> AppResult<Vec<FeatureFlag>> {
+        let rows = sqlx::query_as::<_, FlagRow>(&format!("SELECT {} FROM feature_flags ORDER BY key", FLAG_COLUMNS))
+            .fetch_all(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+
+    async fn get(&self, key: &str) -> AppResult<Option<FeatureFlag>> {
+        let row = sqlx::query_as::<_, FlagRow>(&format!("SELECT {} FROM feature_flags WHERE key = $1", FLAG_COLUMNS))
+            .bind(key)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(row.map(Into::into))
+    }
+
+    async fn create(&self, flag: &FeatureFlag) -> AppResult<bool> {
+        let definition = &flag.definition;
+        let result = sqlx::query(
+            r#"INSERT INTO feature_flags (id, key, description, kind, enabled, bucket_by, rules, fallthrough, version, updated_by, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+            ON CONFLICT (key) DO NOTHING"#
+        )
+        .bind(flag.id)
+        .bind(&definition.key)
+        .bind(&definition.description)
+        .bind(definition.kind)
+        .bind(definition.enabled)
+        .bind(definition.bucket_by)
+        .bind(Json(&definition.rules))
+        .bind(Json(&definition.fallthrough))
+        .bind(flag.version)
+        .bind(flag.updated_by)
+        .bind(flag.updated_at)
+        .execute(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn update(&self, flag: &FeatureFlag) -> AppResult<Option<FeatureFlag>> {
+        let definition = &flag.definition;
+        let row = sqlx::query_as::<_, FlagRow>(&format!(
+            r#"UPDATE feature_flags
+            SET description = $2, kind = $3, enabled = $4, bucket_by = $5, rules = $6, fallthrough = $7,
+                updated_by = $8, updated_at = $9, version = version + 1
+            WHERE key = $1 AND version = $10
+            RETURNING {}"#,
+            FLAG_COLUMNS
+        ))
+        .bind(&definition.key)
+        .bind(&definition.description)
+        .bind(definition.kind)
+        .bind(definition.enabled)
+        .bind(definition.bucket_by)
+        .bind(Json(&definition.rules))
+        .bind(Json(&definition.fallthrough))
+        .bind(flag.updated_by)
+        .bind(flag.updated_at)
+        .bind(flag.version)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(row.map(Into::into))
+    }
+
+    async fn delete(&self, key: &str) -> AppResult<bool> {
+        let result = sqlx::query("DELETE FROM feature_flags WHERE key = $1")
+            .bind(key)
+            .execute(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(result.rows_affected() == 1)
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryFlagStore {
+    flags: Mutex<HashMap<String, FeatureFlag>>,
+}
+
+impl InMemoryFlagStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl FlagStore for InMemoryFlagStore {
+    async fn list(&self) -> AppResult<Vec<FeatureFlag>> {
+        let mut flags: Vec<_> = self.flags.lock().await.values().cloned().collect();
+        flags.sort_by(|a, b| a.definition.key.cmp(&b.definition.key));
+        Ok(flags)
+    }
+
+    async fn get(&self, key: &str) -> AppResult<Option<FeatureFlag>> {
+        Ok(self.flags.lock().await.get(key).cloned())
+    }
+
+    async fn create(&self, flag: &FeatureFlag) -> AppResult<bool> {
+        let mut flags = self.flags.lock().await;
+        if flags.contains_key(&flag.definition.key) {
+            return Ok(false);
+        }
+        flags.insert(flag.definition.key.clone(), flag.clone());
+        Ok(true)
+    }
+
+    async fn update(&self, flag: &FeatureFlag) -> AppResult<Option<FeatureFlag>> {
+        let mut flags = self.flags.lock().await;
+        match flags.get_mut(&flag.definition.key) {
+            Some(current) if current.version == flag.version => {
+                *current = FeatureFlag { version: flag.version + 1, ..flag.clone() };
+                Ok(Some(current.clone()))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> AppResult<bool> {
+        Ok(self.flags.lock().await.remove(key).is_some())
+    }
+}
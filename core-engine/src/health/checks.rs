@@ -0,0 +1,125 @@
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::async_trait;
+use chrono::Utc;
+use sqlx::PgPool;
+use tokio::net::TcpStream;
+
+use super::HealthCheck;
+use crate::agent::context::ContextStore;
+use crate::error::{AppError, AppResult};
+
+pub struct PgPoolCheck {
+    pool: PgPool,
+}
+
+impl PgPoolCheck {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl HealthCheck for PgPoolCheck {
+    async fn check(&self) -> AppResult<()> {
+        sqlx::query("SELECT 1").execute(&self.pool).await?;
+        Ok(())
+    }
+}
+
+pub struct RedisCheck {
+    store: Arc<ContextStore>,
+}
+
+impl RedisCheck {
+    pub fn new(store: Arc<ContextStore>) -> Self {
+        Self { store }
+    }
+}
+
+#[async_trait]
+impl HealthCheck for RedisCheck {
+    async fn check(&self) -> AppResult<()> {
+        self.store.health_check().await
+    }
+}
+
+// Liveness of a listener such as the gRPC server: the port accepts connections
+pub struct TcpCheck {
+    addr: SocketAddr,
+}
+
+impl TcpCheck {
+    pub fn new(addr: SocketAddr) -> Self {
+        Self { addr }
+    }
+}
+
+#[async_trait]
+impl HealthCheck for TcpCheck {
+    async fn check(&self) -> AppResult<()> {
+        TcpStream::connect(self.addr)
+            .await
+            .map(|_| ())
+            .map_err(|e| AppError::Connection(format!("{} is not accepting connections: {}", self.addr, e)))
+    }
+}
+
+// Timestamp a background loop refreshes; a stale beat means the loop or whatever it
+// waits on is stuck
+#[derive(Clone)]
+pub struct Heartbeat {
+    last_beat_ms: Arc<AtomicI64>,
+}
+
+impl Default for Heartbeat {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Heartbeat {
+    pub fn new() -> Self {
+        let heartbeat = Self { last_beat_ms: Arc::new(AtomicI64::new(0)) };
+        heartbeat.beat();
+        heartbeat
+    }
+
+    pub fn beat(&self) {
+        self.last_beat_ms.store(Utc::now().timestamp_millis(), Ordering::Relaxed);
+    }
+
+    pub fn lag(&self) -> Duration {
+        let lag = Utc::now().timestamp_millis() - self.last_beat_ms.load(Ordering::Relaxed);
+        Duration::from_millis(lag.max(0) as u64)
+    }
+}
+
+pub struct HeartbeatCheck {
+    heartbeat: Heartbeat,
+    max_lag: Duration,
+}
+
+impl HeartbeatCheck {
+    pub fn new(heartbeat: Heartbeat, max_lag: Duration) -> Self {
+        Self { heartbeat, max_lag }
+    }
+}
+
+#[async_trait]
+impl HealthCheck for HeartbeatCheck {
+    async fn check(&self) -> AppResult<()> {
+        let lag = self.heartbeat.lag();
+        if lag > self.max_lag {
+            return Err(AppError::Internal(format!(
+                "Heartbeat is {}ms old, limit {}ms",
+                lag.as_millis(),
+                self.max_lag.as_millis()
+            )));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,277 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::{
+    async_trait,
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::json;
+use tokio::sync::Mutex;
+use tracing::warn;
+
+use crate::error::AppResult;
+
+pub mod checks;
+
+pub use checks::{Heartbeat, HeartbeatCheck, PgPoolCheck, RedisCheck, TcpCheck};
+
+const DEFAULT_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(2);
+
+#[async_trait]
+pub trait HealthCheck: Send + Sync {
+    async fn check(&self) -> AppResult<()>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Healthy,
+    // A non-critical check failed; still ready for traffic
+    Degraded,
+    Unhealthy,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckResult {
+    pub status: HealthStatus,
+    pub critical: bool,
+    pub duration_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthReport {
+    pub status: HealthStatus,
+    pub checked_at: DateTime<Utc>,
+    pub checks: BTreeMap<String, CheckResult>,
+}
+
+impl HealthReport {
+    pub fn is_ready(&self) -> bool {
+        self.status != HealthStatus::Unhealthy
+    }
+}
+
+struct RegisteredCheck {
+    name: String,
+    critical: bool,
+    timeout: Duration,
+    check: Arc<dyn HealthCheck>,
+}
+
+pub struct HealthRegistry {
+    checks: Vec<RegisteredCheck>,
+    cache_ttl: Duration,
+    cached: Mutex<Option<(Instant, HealthReport)>>,
+}
+
+impl Default for HealthRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HealthRegistry {
+    pub fn new() -> Self {
+        Self {
+            checks: Vec::new(),
+            cache_ttl: DEFAULT_CACHE_TTL,
+            cached: Mutex::new(None),
+        }
+    }
+
+    // Results are reused for this long so probes from every load balancer node don't each
+    // hit the database and Redis
+    pub fn with_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.cache_ttl = ttl;
+        self
+    }
+
+    // A failing critical check fails readiness
+    pub fn with_critical(self, name: impl Into<String>, check: Arc<dyn HealthCheck>) -> Self {
+        self.with_check(name, true, DEFAULT_CHECK_TIMEOUT, check)
+    }
+
+    // A failing non-critical check only degrades the status
+    pub fn with_non_critical(self, name: impl Into<String>, check: Arc<dyn HealthCheck>) -> Self {
+        self.with_check(name, false, DEFAULT_CHECK_TIMEOUT, check)
+    }
+
+    pub fn with_check(mut self, name: impl Into<String>, critical: bool, timeout: Duration, check: Arc<dyn HealthCheck>) -> Self {
+        self.checks.push(RegisteredCheck { name: name.into(), critical, timeout, check });
+        self
+    }
+
+    async fn run(check: &RegisteredCheck) -> (String, CheckResult) {
+        let started = Instant::now();
+        let error = match tokio::time::timeout(check.timeout, check.check.check()).await {
+            Ok(Ok(())) => None,
+            Ok(Err(e)) => Some(e.to_string()),
+            Err(_) => Some(format!("Timed out after {}ms", check.timeout.as_millis())),
+        };
+        let status = match (&error, check.critical) {
+            (None, _) => HealthStatus::Healthy,
+            (Some(_), true) => HealthStatus::Unhealthy,
+            (Some(_), false) => HealthStatus::Degraded,
+        };
+        if let Some(e) = &error {
+            warn!("Health check {} failed: {}", check.name, e);
+        }
+        let result = CheckResult {
+            status,
+            critical: check.critical,
+            duration_ms: started.elapsed().as_millis() as u64,
+            error,
+        };
+        (check.name.clone(), result)
+    }
+
+    // Runs every check concurrently, or returns the cached report while it's fresh
+    pub async fn readiness(&self) -> HealthReport {
+        let mut cached = self.cached.lock().await;
+        if let Some((at, report)) = cached.as_ref() {
+            if at.elapsed() < self.cache_ttl {
+                return report.clone();
+            }
+        }
+        let checks: BTreeMap<String, CheckResult> =
+            futures::future::join_all(self.checks.iter().map(Self::run)).await.into_iter().collect();
+        let status = checks.values().map(|c| c.status).fold(HealthStatus::Healthy, |worst, status| {
+            match (worst, status) {
+                (HealthStatus::Unhealthy, _) | (_, HealthStatus::Unhealthy) => HealthStatus::Unhealthy,
+                (HealthStatus::Degraded, _) | (_, HealthStatus::Degraded) => HealthStatus::Degraded,
+                _ => HealthStatus::Healthy,
+            }
+        });
+        let report = HealthReport { status, checked_at: Utc::now(), checks };
+        *cached = Some((Instant::now(), report.clone()));
+        report
+    }
+}
+
+pub async fn liveness() -> Json<serde_json::Value> {
+    Json(json!({ "status": "ok" }))
+}
+
+pub async fn readiness(State(registry): State<Arc<HealthRegistry>>) -> Response {
+    let report = registry.readiness().await;
+    let status = if report.is_ready() { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (status, Json(report)).into_response()
+}
+
+// Plain-text form of readiness for probes that only look at `/health`
+pub async fn legacy_health(State(registry): State<Arc<HealthRegistry>>) -> Response {
+    if registry.readiness().await.is_ready() {
+        (StatusCode::OK, "OK").into_response()
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "UNAVAILABLE").into_response()
+    }
+}
+
+pub fn router<S>(registry: Arc<HealthRegistry>) -> Router<S> {
+    Router::new()
+        .route("/health", get(legacy_health))
+        .route("/health/live", get(liveness))
+        .route("/health/ready", get(readiness))
+        .with_state(registry)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::context::ContextStore;
+    use crate::error::AppError;
+    use axum::body::Body;
+    use axum::http::Request;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tower::ServiceExt;
+
+    #[derive(Default)]
+    struct CountingCheck {
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl HealthCheck for CountingCheck {
+        async fn check(&self) -> AppResult<()> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    struct StuckCheck;
+
+    #[async_trait]
+    impl HealthCheck for StuckCheck {
+        async fn check(&self) -> AppResult<()> {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Err(AppError::Internal("unreachable".to_string()))
+        }
+    }
+
+    async fn get_json(app: Router, uri: &str) -> (StatusCode, serde_json::Value) {
+        let response = app
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(serde_json::Value::Null))
+    }
+
+    #[tokio::test]
+    async fn test_down_redis_fails_readiness() {
+        // Nothing listens on port 1, so the ping fails the way it does when Redis is down
+        let redis = Arc::new(ContextStore::new("redis://127.0.0.1:1").unwrap());
+        let database = Arc::new(CountingCheck::default());
+        let registry = HealthRegistry::new()
+            .with_critical("database", database.clone())
+            .with_critical("redis", Arc::new(RedisCheck::new(redis)))
+            .with_check("agent_manager", false, Duration::from_millis(50), Arc::new(StuckCheck))
+            .with_cache_ttl(Duration::from_secs(60));
+        let app: Router = router(Arc::new(registry));
+
+        let (status, body) = get_json(app.clone(), "/health/live").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], "ok");
+
+        let (status, body) = get_json(app.clone(), "/health/ready").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["status"], "unhealthy");
+        assert_eq!(body["checks"]["database"]["status"], "healthy");
+        assert!(body["checks"]["database"].get("error").is_none());
+        assert_eq!(body["checks"]["redis"]["status"], "unhealthy");
+        assert_eq!(body["checks"]["redis"]["critical"], true);
+        assert!(body["checks"]["redis"]["error"].as_str().unwrap().contains("Redis"));
+        assert_eq!(body["checks"]["agent_manager"]["status"], "degraded");
+        assert!(body["checks"]["agent_manager"]["error"].as_str().unwrap().contains("Timed out"));
+
+        // Served from the cache: the database isn't pinged again
+        let response = app
+            .oneshot(Request::builder().uri("/health").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(database.calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_non_critical_failure_only_degrades() {
+        let registry = HealthRegistry::new()
+            .with_critical("database", Arc::new(CountingCheck::default()))
+            .with_check("agent_manager", false, Duration::from_millis(20), Arc::new(StuckCheck))
+            .with_cache_ttl(Duration::ZERO);
+        let (status, body) = get_json(router(Arc::new(registry)), "/health/ready").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], "degraded");
+    }
+}
@@ -0,0 +1,511 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::discovery::{DiscoveredResource, DiscoveryRun};
+use crate::error::{AppError, AppResult};
+use crate::reporting::render::escape;
+use crate::reporting::{Recipient, ReportMessage, ReportNotifier};
+
+pub mod store;
+
+pub use store::{InMemoryInventoryStore, PgInventoryStore};
+
+pub const DEFAULT_PAGE_SIZE: i64 = 50;
+pub const MAX_PAGE_SIZE: i64 = 500;
+// New matches listed in one notification; the rest are summarized as a count
+const NOTIFY_LISTED: usize = 20;
+
+// The current state of one resource, kept up to date from each provider account's latest run
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct InventoryResource {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub provider: String,
+    pub account_id: String,
+    pub resource_key: String,
+    pub region: String,
+    pub resource_type: String,
+    pub name: Option<String>,
+    // Read from the `state` attribute
+    pub state: Option<String>,
+    pub tags: BTreeMap<String, String>,
+    pub attributes: BTreeMap<String, Value>,
+    pub first_seen_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+}
+
+impl InventoryResource {
+    pub fn from_discovered(run: &DiscoveryRun, resource: &DiscoveredResource) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            tenant_id: run.tenant_id,
+            provider: run.provider.clone(),
+            account_id: run.account_id.clone(),
+            resource_key: resource.resource_key.clone(),
+            region: resource.region.clone(),
+            resource_type: resource.resource_type.clone(),
+            name: resource.name.clone(),
+            state: resource.attributes.get("state").and_then(Value::as_str).map(str::to_string),
+            tags: resource.tags.clone(),
+            attributes: resource.attributes.clone(),
+            first_seen_at: run.completed_at,
+            last_seen_at: run.completed_at,
+        }
+    }
+
+    fn same_content(&self, other: &Self) -> bool {
+        (&self.resource_type, &self.name, &self.tags, &self.attributes)
+            == (&other.resource_type, &other.name, &other.tags, &other.attributes)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InventorySort {
+    #[default]
+    Name,
+    ResourceType,
+    Region,
+    LastSeen,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum SortKey {
+    Text(String),
+    Time(DateTime<Utc>),
+}
+
+impl InventorySort {
+    fn key(&self, resource: &InventoryResource) -> SortKey {
+        match self {
+            InventorySort::Name => SortKey::Text(resource.name.clone().unwrap_or_default()),
+            InventorySort::ResourceType => SortKey::Text(resource.resource_type.clone()),
+            InventorySort::Region => SortKey::Text(resource.region.clone()),
+            InventorySort::LastSeen => SortKey::Time(resource.last_seen_at),
+        }
+    }
+}
+
+// Every set filter must match. `text` is a case-insensitive substring of the name or resource key;
+// `attributes` compares top-level values for equality.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct InventoryQuery {
+    #[serde(default)]
+    pub provider: Option<String>,
+    #[serde(default)]
+    pub resource_type: Option<String>,
+    #[serde(default)]
+    pub region: Option<String>,
+    #[serde(default)]
+    pub state: Option<String>,
+    #[serde(default)]
+    pub tags: BTreeMap<String, String>,
+    #[serde(default)]
+    pub attributes: BTreeMap<String, Value>,
+    #[serde(default)]
+    pub text: Option<String>,
+    #[serde(default)]
+    pub sort: InventorySort,
+    #[serde(default)]
+    pub descending: bool,
+}
+
+impl InventoryQuery {
+    pub fn matches(&self, resource: &InventoryResource) -> bool {
+        let equals = |filter: &Option<String>, value: &str| filter.as_deref().is_none_or(|f| f == value);
+        equals(&self.provider, &resource.provider)
+            && equals(&self.resource_type, &resource.resource_type)
+            && equals(&self.region, &resource.region)
+            && self.state.as_deref().is_none_or(|s| resource.state.as_deref() == Some(s))
+            && self.tags.iter().all(|(k, v)| resource.tags.get(k) == Some(v))
+            && self.attributes.iter().all(|(k, v)| resource.attributes.get(k) == Some(v))
+            && self.text.as_deref().is_none_or(|text| {
+                let text = text.to_lowercase();
+                resource.resource_key.to_lowercase().contains(&text)
+                    || resource.name.as_deref().is_some_and(|n| n.to_lowercase().contains(&text))
+            })
+    }
+
+    // Orders by the sort key, then id, so pages don't overlap when keys tie
+    fn compare(&self, a: &InventoryResource, b: &InventoryResource) -> Ordering {
+        let order = self.sort.key(a).cmp(&self.sort.key(b)).then_with(|| a.id.cmp(&b.id));
+        if self.descending {
+            order.reverse()
+        } else {
+            order
+        }
+    }
+
+    fn validate(&self) -> AppResult<()> {
+        if self.text.as_deref().is_some_and(|t| t.trim().is_empty()) {
+            return Err(AppError::Validation("Search text must not be blank".into()));
+        }
+        if self.tags.keys().any(|k| k.is_empty()) {
+            return Err(AppError::Validation("Tag filters need a key".into()));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InventoryPage {
+    pub resources: Vec<InventoryResource>,
+    // Pass back as `after` for the next page; absent on the last page
+    pub next_cursor: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct InventoryChange {
+    pub before: Option<InventoryResource>,
+    pub after: InventoryResource,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct IngestReport {
+    // Added or changed by the run; unchanged resources are not listed
+    pub changes: Vec<InventoryChange>,
+    pub removed: u64,
+    // The inventory already reflects a later run of the same account
+    pub stale: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SavedSearch {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub name: String,
+    pub query: InventoryQuery,
+    // Told about resources that start matching after a discovery run; none means no notifications
+    pub recipients: Vec<Recipient>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewSavedSearch {
+    pub name: String,
+    pub query: InventoryQuery,
+    #[serde(default)]
+    pub recipients: Vec<Recipient>,
+}
+
+#[async_trait]
+pub trait InventoryStore: Send + Sync {
+    // Replaces the run's provider account with the run's resources, keeping ids and first-seen
+    // times of resources seen before
+    async fn ingest(&self, run: &DiscoveryRun, resources: &[DiscoveredResource]) -> AppResult<IngestReport>;
+    async fn search(
+        &self,
+        tenant_id: Uuid,
+        query: &InventoryQuery,
+        after: Option<Uuid>,
+        limit: i64,
+    ) -> AppResult<Vec<InventoryResource>>;
+    async fn resource(
+        &self,
+        tenant_id: Uuid,
+        provider: &str,
+        account_id: &str,
+        resource_key: &str,
+        region: &str,
+    ) -> AppResult<Option<InventoryResource>>;
+    // Incremental changes between discovery runs; the account's next run still replaces them
+    async fn put_resource(&self, resource: &InventoryResource) -> AppResult<()>;
+    async fn remove_resource(
+        &self,
+        tenant_id: Uuid,
+        provider: &str,
+        account_id: &str,
+        resource_key: &str,
+        region: &str,
+    ) -> AppResult<bool>;
+    async fn create_saved_search(&self, search: &SavedSearch) -> AppResult<()>;
+    async fn saved_searches(&self, tenant_id: Uuid) -> AppResult<Vec<SavedSearch>>;
+    async fn saved_search(&self, tenant_id: Uuid, id: Uuid) -> AppResult<Option<SavedSearch>>;
+    async fn delete_saved_search(&self, tenant_id: Uuid, id: Uuid) -> AppResult<bool>;
+}
+
+pub struct InventoryService {
+    store: Arc<dyn InventoryStore>,
+    notifier: Option<Arc<dyn ReportNotifier>>,
+}
+
+impl InventoryService {
+    pub fn new(store: Arc<dyn InventoryStore>) -> Self {
+        Self { store, notifier: None }
+    }
+
+    // Saved-search notifications go out over the same channels as scheduled reports
+    pub fn with_notifier(mut self, notifier: Arc<dyn ReportNotifier>) -> Self {
+        self.notifier = Some(notifier);
+        self
+    }
+
+    pub async fn search(&self, tenant_id: Uuid, query: &InventoryQuery, after: Option<Uuid>, limit: i64) -> AppResult<InventoryPage> {
+        query.validate()?;
+        let limit = limit.clamp(1, MAX_PAGE_SIZE);
+        let mut resources = self.store.search(tenant_id, query, after, limit + 1).await?;
+        let next_cursor = if resources.len() as i64 > limit {
+            resources.truncate(limit as usize);
+            resources.last().map(|r| r.id)
+        } else {
+            None
+        };
+        Ok(InventoryPage { resources, next_cursor })
+    }
+
+    pub async fn resource(
+        &self,
+        tenant_id: Uuid,
+        provider: &str,
+        account_id: &str,
+        resource_key: &str,
+        region: &str,
+    ) -> AppResult<Option<InventoryResource>> {
+        self.store.resource(tenant_id, provider, account_id, resource_key, region).await
+    }
+
+    pub async fn put_resource(&self, resource: &InventoryResource) -> AppResult<()> {
+        self.store.put_resource(resource).await
+    }
+
+    pub async fn remove_resource(&self, resource: &InventoryResource) -> AppResult<bool> {
+        self.store
+            .remove_resource(resource.tenant_id, &resource.provider, &resource.account_id, &resource.resource_key, &resource.region)
+            .await
+    }
+
+    pub async fn create_saved_search(&self, tenant_id: Uuid, search: NewSavedSearch, now: DateTime<Utc>) -> AppResult<SavedSearch> {
+        if search.name.trim().is_empty() {
+            return Err(AppError::Validation("Saved search name is required".into()));
+        }
+        search.query.validate()?;
+        let saved = SavedSearch {
+            id: Uuid::new_v4(),
+            tenant_id,
+            name: search.name,
+            query: search.query,
+            recipients: search.recipients,
+            created_at: now,
+        };
+        self.store.create_saved_search(&saved).await?;
+        Ok(saved)
+    }
+
+    pub async fn saved_searches(&self, tenant_id: Uuid) -> AppResult<Vec<SavedSearch>> {
+        self.store.saved_searches(tenant_id).await
+    }
+
+    pub async fn saved_search(&self, tenant_id: Uuid, id: Uuid) -> AppResult<SavedSearch> {
+        self.store
+            .saved_search(tenant_id, id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Saved search not found".into()))
+    }
+
+    pub async fn delete_saved_search(&self, tenant_id: Uuid, id: Uuid) -> AppResult<()> {
+        if !self.store.delete_saved_search(tenant_id, id).await? {
+            return Err(AppError::NotFound("Saved search not found".into()));
+        }
+        Ok(())
+    }
+
+    // Called after each discovery run; saved searches hear about resources that match now but
+    // didn't before the run
+    pub async fn ingest(&self, run: &DiscoveryRun, resources: &[DiscoveredResource]) -> AppResult<IngestReport> {
+        let report = self.store.ingest(run, resources).await?;
+        if report.stale {
+            info!("Skipped inventory update from run {}: a later run is already reflected", run.id);
+            return Ok(report);
+        }
+        for search in self.store.saved_searches(run.tenant_id).await? {
+            if search.recipients.is_empty() {
+                continue;
+            }
+            let matches = new_matches(&search.query, &report.changes);
+            if !matches.is_empty() {
+                self.notify(&search, &matches).await;
+            }
+        }
+        Ok(report)
+    }
+
+    async fn notify(&self, search: &SavedSearch, matches: &[&InventoryResource]) {
+        let message = match_message(search, matches);
+        for recipient in &search.recipients {
+            let result = match &self.notifier {
+                Some(notifier) => notifier.deliver(recipient, &message).await,
+                None => Err(AppError::Configuration("No notifier configured for saved searches".into())),
+            };
+            if let Err(e) = result {
+                warn!("Failed to notify {:?} about saved search {}: {}", recipient, search.id, e);
+            }
+        }
+    }
+}
+
+pub fn new_matches<'a>(query: &InventoryQuery, changes: &'a [InventoryChange]) -> Vec<&'a InventoryResource> {
+    changes
+        .iter()
+        .filter(|c| query.matches(&c.after) && !c.before.as_ref().is_some_and(|b| query.matches(b)))
+        .map(|c| &c.after)
+        .collect()
+}
+
+fn match_message(search: &SavedSearch, matches: &[&InventoryResource]) -> ReportMessage {
+    let mut html = format!("<h1>{}</h1><ul>", escape(&search.name));
+    for resource in matches.iter().take(NOTIFY_LISTED) {
+        html.push_str(&format!(
+            "<li>{} ({}, {})</li>",
+            escape(resource.name.as_deref().unwrap_or(&resource.resource_key)),
+            escape(&resource.resource_type),
+            escape(&resource.region),
+        ));
+    }
+    html.push_str("</ul>");
+    if matches.len() > NOTIFY_LISTED {
+        html.push_str(&format!("<p>and {} more</p>", matches.len() - NOTIFY_LISTED));
+    }
+    ReportMessage {
+        subject: format!("{}: {} new matching resources", search.name, matches.len()),
+        html,
+        attachments: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+    use serde_json::json;
+    use std::sync::Mutex;
+
+    const TYPES: &[&str] = &["m5.xlarge", "t3.medium", "c6g.large", "r5.2xlarge"];
+    const REGIONS: &[&str] = &["us-east-1", "us-west-2", "eu-west-1"];
+    const ENVS: &[&str] = &["prod", "staging", "dev", "test", "sandbox"];
+
+    fn run(tenant_id: Uuid, at: DateTime<Utc>) -> DiscoveryRun {
+        DiscoveryRun {
+            id: Uuid::new_v4(),
+            tenant_id,
+            provider: "aws".into(),
+            account_id: "123456789012".into(),
+            resource_count: 0,
+            started_at: at,
+            completed_at: at,
+        }
+    }
+
+    fn synthetic(count: usize) -> Vec<DiscoveredResource> {
+        (0..count)
+            .map(|i| {
+                let region = REGIONS[i % REGIONS.len()];
+                DiscoveredResource::new("ec2_instance", &format!("i-{:08x}", i), None, region)
+                    .with_name(Some(format!("web-{}", i)))
+                    .with_tags([("env".to_string(), ENVS[i % ENVS.len()].to_string())])
+                    .with_attribute("instance_type", TYPES[i % TYPES.len()])
+                    .with_attribute("state", if i % 10 == 0 { "stopped" } else { "running" })
+            })
+            .collect()
+    }
+
+    #[derive(Default)]
+    struct RecordingNotifier(Mutex<Vec<ReportMessage>>);
+
+    #[async_trait]
+    impl ReportNotifier for RecordingNotifier {
+        async fn deliver(&self, _: &Recipient, message: &ReportMessage) -> AppResult<()> {
+            self.0.lock().unwrap().push(message.clone());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_structured_and_text_search_over_large_inventory() {
+        let service = InventoryService::new(Arc::new(InMemoryInventoryStore::new()));
+        let tenant = Uuid::new_v4();
+        let resources = synthetic(12_000);
+        let at = Utc.with_ymd_and_hms(2025, 7, 1, 0, 0, 0).unwrap();
+        let report = service.ingest(&run(tenant, at), &resources).await.unwrap();
+        assert_eq!(report.changes.len(), 12_000);
+
+        // All m5.xlarge in us-east-1 tagged env=prod and running
+        let query = InventoryQuery {
+            region: Some("us-east-1".into()),
+            state: Some("running".into()),
+            tags: BTreeMap::from([("env".to_string(), "prod".to_string())]),
+            attributes: BTreeMap::from([("instance_type".to_string(), json!("m5.xlarge"))]),
+            ..Default::default()
+        };
+        let expected: Vec<usize> = (0..12_000).filter(|i| i % 3 == 0 && i % 5 == 0 && i % 4 == 0 && i % 10 != 0).collect();
+        let mut seen = Vec::new();
+        let mut after = None;
+        loop {
+            let page = service.search(tenant, &query, after, 75).await.unwrap();
+            seen.extend(page.resources.iter().map(|r| r.name.clone().unwrap()));
+            match page.next_cursor {
+                Some(cursor) => after = Some(cursor),
+                None => break,
+            }
+        }
+        let mut want: Vec<String> = expected.iter().map(|i| format!("web-{}", i)).collect();
+        want.sort();
+        assert_eq!(seen, want);
+
+        let text = InventoryQuery { text: Some("I-0000002A".into()), ..Default::default() };
+        let page = service.search(tenant, &text, None, 10).await.unwrap();
+        assert_eq!(page.resources.len(), 1);
+        assert_eq!(page.resources[0].name.as_deref(), Some("web-42"));
+
+        let newest = InventoryQuery { sort: InventorySort::Region, descending: true, ..Default::default() };
+        let page = service.search(tenant, &newest, None, 5).await.unwrap();
+        assert!(page.resources.iter().all(|r| r.region == "us-west-2"));
+        assert!(service.search(Uuid::new_v4(), &query, None, 10).await.unwrap().resources.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_saved_search_notifies_on_new_matches_only() {
+        let notifier = Arc::new(RecordingNotifier::default());
+        let service = InventoryService::new(Arc::new(InMemoryInventoryStore::new())).with_notifier(notifier.clone());
+        let tenant = Uuid::new_v4();
+        let at = Utc.with_ymd_and_hms(2025, 7, 1, 0, 0, 0).unwrap();
+        let resources = synthetic(10);
+        let first = service.ingest(&run(tenant, at), &resources).await.unwrap();
+
+        let prod = InventoryQuery { tags: BTreeMap::from([("env".to_string(), "prod".to_string())]), ..Default::default() };
+        let search = NewSavedSearch {
+            name: "prod <fleet>".into(),
+            query: prod,
+            recipients: vec![Recipient::Slack { channel_id: "C123".into() }],
+        };
+        let saved = service.create_saved_search(tenant, search, at).await.unwrap();
+        let before = &first.changes.iter().find(|c| c.after.name.as_deref() == Some("web-0")).unwrap().after;
+
+        // web-1 is retagged prod and web-10 is new; web-0 and web-5 were prod already
+        let mut next = synthetic(11);
+        next[1].tags.insert("env".into(), "prod".into());
+        let later = at + Duration::hours(1);
+        let report = service.ingest(&run(tenant, later), &next).await.unwrap();
+        assert_eq!(report.changes.len(), 2);
+        let sent = notifier.0.lock().unwrap().clone();
+        assert_eq!(sent.len(), 1);
+        assert!(sent[0].subject.contains("2 new matching"));
+        assert!(sent[0].html.contains("prod &lt;fleet&gt;"));
+
+        // Ids and first-seen times survive; dropped resources leave the inventory
+        let page = service.search(tenant, &saved.query, None, 10).await.unwrap();
+        let web0 = page.resources.iter().find(|r| r.name.as_deref() == Some("web-0")).unwrap();
+        assert_eq!((web0.id, web0.first_seen_at, web0.last_seen_at), (before.id, at, later));
+        let shrunk = service.ingest(&run(tenant, later + Duration::hours(1)), &next[..4]).await.unwrap();
+        assert_eq!(shrunk.removed, 7);
+        assert!(service.ingest(&run(tenant, at), &resources).await.unwrap().stale);
+        assert_eq!(notifier.0.lock().unwrap().len(), 1);
+    }
+}
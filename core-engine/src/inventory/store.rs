@@ -0,0 +1,627 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use sqlx::types::Json;
+use sqlx::{PgPool, Postgres, QueryBuilder}; // CockroachDB uses PostgreSQL protocol
+use uuid::Uuid;
+
+use super::{IngestReport, InventoryChange, InventoryQuery, InventoryResource, InventorySort, InventoryStore, SavedSearch};
+use crate::discovery::{DiscoveredResource, DiscoveryRun};
+use crate::error::{AppError, AppResult};
+use crate::reporting::Recipient;
+
+// Rows per upsert when ingesting a run
+const UPSERT_BATCH: usize = 500;
+
+const RESOURCE_COLUMNS: &str = "id, tenant_id, provider, account_id, resource_key, region, resource_type, name, \
+    state, tags, attributes, first_seen_at, last_seen_at";
+
+#[derive(sqlx::FromRow)]
+struct ResourceRow {
+    id: Uuid,
+    tenant_id: Uuid,
+    provider: String,
+    account_id: String,
+    resource_key: String,
+    region: String,
+    resource_type: String,
+    name: Option<String>,
+    state: Option<String>,
+    tags: Json<BTreeMap<String, String>>,
+    attributes: Json<BTreeMap<String, Value>>,
+    first_seen_at: DateTime<Utc>,
+    last_seen_at: DateTime<Utc>,
+}
+
+impl From<ResourceRow> for InventoryResource {
+    fn from(row: ResourceRow) -> Self {
+        Self {
+            id: row.id,
+            tenant_id: row.tenant_id,
+            provider: row.provider,
+            account_id: row.account_id,
+            resource_key: row.resource_key,
+            region: row.region,
+            resource_type: row.resource_type,
+            name: row.name,
+            state: row.state,
+            tags: row.tags.0,
+            attributes: row.attributes.0,
+            first_seen_at: row.first_seen_at,
+            last_seen_at: row.last_seen_at,
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct SavedSearchRow {
+    id: Uuid,
+    tenant_id: Uuid,
+    name: String,
+    query: Json<InventoryQuery>,
+    recipients: Json<Vec<Recipient>>,
+    created_at: DateTime<Utc>,
+}
+
+impl From<SavedSearchRow> for SavedSearch {
+    fn from(row: SavedSearchRow) -> Self {
+        Self {
+            id: row.id,
+            tenant_id: row.tenant_id,
+            name: row.name,
+            query: row.query.0,
+            recipients: row.recipients.0,
+            created_at: row.created_at,
+        }
+    }
+}
+
+// Carries ids and first-seen times over from what the inventory held, and lists what changed
+fn reconcile(
+    run: &DiscoveryRun,
+    resources: &[DiscoveredResource],
+    mut existing: HashMap<(String, String), InventoryResource>,
+) -> (Vec<InventoryResource>, Vec<InventoryChange>) {
+    let mut current = Vec::with_capacity(resources.len());
+    let mut changes = Vec::new();
+    for resource in resources {
+        let mut after = InventoryResource::from_discovered(run, resource);
+        let before = existing.remove(&(after.resource_key.clone(), after.region.clone()));
+        if let Some(before) = &before {
+            after.id = before.id;
+            after.first_seen_at = before.first_seen_at;
+        }
+        if !before.as_ref().is_some_and(|b| b.same_content(&after)) {
+            changes.push(InventoryChange { before, after: after.clone() });
+        }
+        current.push(after);
+    }
+    (current, changes)
+}
+
+fn like_pattern(text: &str) -> String {
+    let escaped = text.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+fn sort_column(sort: InventorySort) -> &'static str {
+    match sort {
+        InventorySort::Name => "COALESCE(name, '')",
+        InventorySort::ResourceType => "resource_type",
+        InventorySort::Region => "region",
+        InventorySort::LastSeen => "last_seen_at",
+    }
+}
+
+// The WHERE clause for a search; the JSONB and trigram filters are served by the inverted indexes
+fn push_filters<'a>(builder: &mut QueryBuilder<'a, Postgres>, tenant_id: Uuid, query: &'a InventoryQuery) {
+    builder.push(" WHERE tenant_id = ").push_bind(tenant_id);
+    for (column, value) in [
+        ("provider", &query.provider),
+        ("resource_type", &query.resource_type),
+        ("region", &query.region),
+        ("state", &query.state),
+    ] {
+        if let Some(value) = value {
+            builder.push(format!(" AND {} = ", column)).push_bind(value);
+        }
+    }
+    if !query.tags.is_empty() {
+        builder.push(" AND tags @> ").push_bind(Json(&query.tags));
+    }
+    if !query.attributes.is_empty() {
+        builder.push(" AND attributes @> ").push_bind(Json(&query.attributes));
+    }
+    if let Some(text) = &query.text {
+        let pattern = like_pattern(text);
+        builder
+            .push(" AND (name ILIKE ")
+            .push_bind(pattern.clone())
+            .push(" OR resource_key ILIKE ")
+            .push_bind(pattern)
+            .push(")");
+    }
+}
+
+fn search_query<'a>(tenant_id: Uuid, query: &'a InventoryQuery, after: Option<Uuid>, limit: i64) -> QueryBuilder<'a, Postgres> {
+    let sort = sort_column(query.sort);
+    let (direction, comparison) = if query.descending { ("DESC", "<") } else { ("ASC", ">") };
+    let mut builder = QueryBuilder::new(format!("SELECT {} FROM inventory_resources", RESOURCE_COLUMNS));
+    push_filters(&mut builder, tenant_id, query);
+    if let Some(after) = after {
+        builder
+            .push(format!(" AND ({}, id) {} (SELECT {}, id FROM inventory_resources WHERE tenant_id = ", sort, comparison, sort))
+            .push_bind(tenant_id)
+            .push(" AND id = ")
+            .push_bind(after)
+            .push(")");
+    }
+    builder
+        .push(format!(" ORDER BY {} {}, id {} LIMIT ", sort, direction, direction))
+        .push_bind(limit);
+    builder
+}
+
+pub struct PgInventoryStore {
+    pool: PgPool,
+}
+
+impl PgInventoryStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl InventoryStore for PgInventoryStore {
+    async fn ingest(&self, run: &DiscoveryRun, resources: &[DiscoveredResource]) -> AppResult<IngestReport> {
+        let mut tx = self.pool.begin().await.map_err(AppError::Database)?;
+        // Runs can be recorded out of order; the newest completed run of an account wins
+        let latest: Option<DateTime<Utc>> = sqlx::query_scalar(
+            r#"SELECT completed_at FROM inventory_accounts
+            WHERE tenant_id = $1 AND provider = $2 AND account_id = $3 FOR UPDATE"#
+        )
+        .bind(run.tenant_id)
+        .bind(&run.provider)
+        .bind(&run.account_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(AppError::Database)?;
+        if latest.is_some_and(|latest| latest > run.completed_at) {
+            return Ok(IngestReport { stale: true, ..Default::default() });
+        }
+        sqlx::query(
+            r#"UPSERT INTO inventory_accounts (tenant_id, provider, account_id, run_id, completed_at)
+            VALUES ($1, $2, $3, $4, $5)"#
+        )
+        .bind(run.tenant_id)
+        .bind(&run.provider)
+        .bind(&run.account_id)
+        .bind(run.id)
+        .bind(run.completed_at)
+        .execute(&mut *tx)
+        .await
+        .map_err(AppError::Database)?;
+
+        let existing = sqlx::query_as::<_, ResourceRow>(&format!(
+            "SELECT {} FROM inventory_resources WHERE tenant_id = $1 AND provider = $2 AND account_id = $3",
+            RESOURCE_COLUMNS
+        ))
+        .bind(run.tenant_id)
+        .bind(&run.provider)
+        .bind(&run.account_id)
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(AppError::Database)?
+        .into_iter()
+        .map(|row| ((row.resource_key.clone(), row.region.clone()), InventoryResource::from(row)))
+        .collect();
+        let (current, changes) = reconcile(run, resources, existing);
+
+        for batch in current.chunks(UPSERT_BATCH) {
+            let mut upsert = QueryBuilder::new(format!("INSERT INTO inventory_resources ({}, last_run_id) ", RESOURCE_COLUMNS));
+            upsert.push_values(batch, |mut row, resource| {
+                row.push_bind(resource.id)
+                    .push_bind(resource.tenant_id)
+                    .push_bind(&resource.provider)
+                    .push_bind(&resource.account_id)
+                    .push_bind(&resource.resource_key)
+                    .push_bind(&resource.region)
+                    .push_bind(&resource.resource_type)
+                    .push_bind(&resource.name)
+                    .push_bind(&resource.state)
+                    .push_bind(Json(&resource.tags))
+                    .push_bind(Json(&resource.attributes))
+                    .push_bind(resource.first_seen_at)
+                    .push_bind(resource.last_seen_at)
+                    .push_bind(run.id);
+            });
+            upsert.push(
+                " ON CONFLICT (tenant_id, provider, account_id, resource_key, region) DO UPDATE SET \
+                resource_type = excluded.resource_type, name = excluded.name, state = excluded.state, \
+                tags = excluded.tags, attributes = excluded.attributes, \
+                last_seen_at = excluded.last_seen_at, last_run_id = excluded.last_run_id",
+            );
+            upsert.build().execute(&mut *tx).await.map_err(AppError::Database)?;
+        }
+
+        let removed = sqlx::query(
+            r#"DELETE FROM inventory_resources
+            WHERE tenant_id = $1 AND provider = $2 AND account_id = $3 AND last_run_id <> $4"#
+        )
+        .bind(run.tenant_id)
+        .bind(&run.provider)
+        .bind(&run.account_id)
+        .bind(run.id)
+        .execute(&mut *tx)
+        .await
+        .map_err(AppError::Database)?
+        .rows_affected();
+        tx.commit().await.map_err(AppError::Database)?;
+
+        Ok(IngestReport { changes, removed, stale: false })
+    }
+
+    async fn search(
+        &self,
+        tenant_id: Uuid,
+        query: &InventoryQuery,
+        after: Option<Uuid>,
+        limit: i64,
+    ) -> AppResult<Vec<InventoryResource>> {
+        let rows = search_query(tenant_id, query, after, limit)
+            .build_query_as::<ResourceRow>()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(rows.into_iter().map(InventoryResource::from).collect())
+    }
+
+    async fn resource(
+        &self,
+        tenant_id: Uuid,
+        provider: &str,
+        account_id: &str,
+        resource_key: &str,
+        region: &str,
+    ) -> AppResult<Option<InventoryResource>> {
+        let row = sqlx::query_as::<_, ResourceRow>(&format!(
+            r#"SELECT {} FROM inventory_resources
+            WHERE tenant_id = $1 AND provider = $2 AND account_id = $3 AND resource_key = $4 AND region = $5"#,
+            RESOURCE_COLUMNS
+        ))
+        .bind(tenant_id)
+        .bind(provider)
+        .bind(account_id)
+        .bind(resource_key)
+        .bind(region)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(row.map(InventoryResource::from))
+    }
+
+    async fn put_resource(&self, resource: &InventoryResource) -> AppResult<()> {
+        // Tagged with the account's latest run so the next run keeps or removes it like any other;
+        // before the account's first run the nil id is replaced all the same
+        sqlx::query(&format!(
+            r#"INSERT INTO inventory_resources ({}, last_run_id)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, COALESCE(
+                (SELECT run_id FROM inventory_accounts WHERE tenant_id = $2 AND provider = $3 AND account_id = $4),
+                '00000000-0000-0000-0000-000000000000'::UUID))
+            ON CONFLICT (tenant_id, provider, account_id, resource_key, region) DO UPDATE SET
+            resource_type = excluded.resource_type, name = excluded.name, state = excluded.state,
+            tags = excluded.tags, attributes = excluded.attributes, last_seen_at = excluded.last_seen_at"#,
+            RESOURCE_COLUMNS
+        ))
+        .bind(resource.id)
+        .bind(resource.tenant_id)
+        .bind(&resource.provider)
+        .bind(&resource.account_id)
+        .bind(&resource.resource_key)
+        .bind(&resource.region)
+        .bind(&resource.resource_type)
+        .bind(&resource.name)
+        .bind(&resource.state)
+        .bind(Json(&resource.tags))
+        .bind(Json(&resource.attributes))
+        .bind(resource.first_seen_at)
+        .bind(resource.last_seen_at)
+        .execute(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(())
+    }
+
+    async fn remove_resource(
+        &self,
+        tenant_id: Uuid,
+        provider: &str,
+        account_id: &str,
+        resource_key: &str,
+        region: &str,
+    ) -> AppResult<bool> {
+        let result = sqlx::query(
+            r#"DELETE FROM inventory_resources
+            WHERE tenant_id = $1 AND provider = $2 AND account_id = $3 AND resource_key = $4 AND region = $5"#
+        )
+        .bind(tenant_id)
+        .bind(provider)
+        .bind(account_id)
+        .bind(resource_key)
+        .bind(region)
+        .execute(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn create_saved_search(&self, search: &SavedSearch) -> AppResult<()> {
+        sqlx::query(
+            r#"INSERT INTO inventory_saved_searches (id, tenant_id, name, query, recipients, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6)"#
+        )
+        .bind(search.id)
+        .bind(search.tenant_id)
+        .bind(&search.name)
+        .bind(Json(&search.query))
+        .bind(Json(&search.recipients))
+        .bind(search.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(())
+    }
+
+    async fn saved_searches(&self, tenant_id: Uuid) -> AppResult<Vec<SavedSearch>> {
+        let rows = sqlx::query_as::<_, SavedSearchRow>(
+            r#"SELECT id, tenant_id, name, query, recipients, created_at
+            FROM inventory_saved_searches WHERE tenant_id = $1 ORDER BY created_at"#
+        )
+        .bind(tenant_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(rows.into_iter().map(SavedSearch::from).collect())
+    }
+
+    async fn saved_search(&self, tenant_id: Uuid, id: Uuid) -> AppResult<Option<SavedSearch>> {
+        let row = sqlx::query_as::<_, SavedSearchRow>(
+            r#"SELECT id, tenant_id, name, query, recipients, created_at
+            FROM inventory_saved_searches WHERE tenant_id = $1 AND id = $2"#
+        )
+        .bind(tenant_id)
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(row.map(SavedSearch::from))
+    }
+
+    async fn delete_saved_search(&self, tenant_id: Uuid, id: Uuid) -> AppResult<bool> {
+        let result = sqlx::query("DELETE FROM inventory_saved_searches WHERE tenant_id = $1 AND id = $2")
+            .bind(tenant_id)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+type AccountKey = (Uuid, String, String);
+
+#[derive(Default)]
+struct InventoryState {
+    accounts: HashMap<AccountKey, DateTime<Utc>>,
+    resources: HashMap<AccountKey, Vec<InventoryResource>>,
+    saved: Vec<SavedSearch>,
+}
+
+#[derive(Default)]
+pub struct InMemoryInventoryStore {
+    state: Mutex<InventoryState>,
+}
+
+impl InMemoryInventoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl InventoryStore for InMemoryInventoryStore {
+    async fn ingest(&self, run: &DiscoveryRun, resources: &[DiscoveredResource]) -> AppResult<IngestReport> {
+        let key = (run.tenant_id, run.provider.clone(), run.account_id.clone());
+        let mut state = self.state.lock().unwrap();
+        if state.accounts.get(&key).is_some_and(|latest| *latest > run.completed_at) {
+            return Ok(IngestReport { stale: true, ..Default::default() });
+        }
+        state.accounts.insert(key.clone(), run.completed_at);
+        let existing: HashMap<_, _> = state
+            .resources
+            .remove(&key)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|r| ((r.resource_key.clone(), r.region.clone()), r))
+            .collect();
+        let previous = existing.len();
+        let (current, changes) = reconcile(run, resources, existing);
+        let kept = current.len() - changes.iter().filter(|c| c.before.is_none()).count();
+        state.resources.insert(key, current);
+        Ok(IngestReport { changes, removed: (previous - kept) as u64, stale: false })
+    }
+
+    async fn search(
+        &self,
+        tenant_id: Uuid,
+        query: &InventoryQuery,
+        after: Option<Uuid>,
+        limit: i64,
+    ) -> AppResult<Vec<InventoryResource>> {
+        let state = self.state.lock().unwrap();
+        let mut matches: Vec<&InventoryResource> = state
+            .resources
+            .iter()
+            .filter(|((tenant, _, _), _)| *tenant == tenant_id)
+            .flat_map(|(_, resources)| resources)
+            .filter(|r| query.matches(r))
+            .collect();
+        matches.sort_by(|a, b| query.compare(a, b));
+        let start = match after {
+            Some(after) => match state.resources.values().flatten().find(|r| r.id == after && r.tenant_id == tenant_id) {
+                Some(cursor) => matches.partition_point(|r| query.compare(r, cursor).is_le()),
+                None => return Ok(Vec::new()),
+            },
+            None => 0,
+        };
+        Ok(matches.into_iter().skip(start).take(limit.max(0) as usize).cloned().collect())
+    }
+
+    async fn resource(
+        &self,
+        tenant_id: Uuid,
+        provider: &str,
+        account_id: &str,
+        resource_key: &str,
+        region: &str,
+    ) -> AppResult<Option<InventoryResource>> {
+        let state = self.state.lock().unwrap();
+        let key = (tenant_id, provider.to_string(), account_id.to_string());
+        Ok(state
+            .resources
+            .get(&key)
+            .and_then(|resources| resources.iter().find(|r| r.resource_key == resource_key && r.region == region))
+            .cloned())
+    }
+
+    async fn put_resource(&self, resource: &InventoryResource) -> AppResult<()> {
+        let key = (resource.tenant_id, resource.provider.clone(), resource.account_id.clone());
+        let mut state = self.state.lock().unwrap();
+        let resources = state.resources.entry(key).or_default();
+        match resources.iter_mut().find(|r| r.resource_key == resource.resource_key && r.region == resource.region) {
+            Some(existing) => *existing = InventoryResource { id: existing.id, first_seen_at: existing.first_seen_at, ..resource.clone() },
+            None => resources.push(resource.clone()),
+        }
+        Ok(())
+    }
+
+    async fn remove_resource(
+        &self,
+        tenant_id: Uuid,
+        provider: &str,
+        account_id: &str,
+        resource_key: &str,
+        region: &str,
+    ) -> AppResult<bool> {
+        let key = (tenant_id, provider.to_string(), account_id.to_string());
+        let mut state = self.state.lock().unwrap();
+        let Some(resources) = state.resources.get_mut(&key) else { return Ok(false) };
+        let before = resources.len();
+        resources.retain(|r| !(r.resource_key == resource_key && r.region == region));
+        Ok(resources.len() < before)
+    }
+
+    async fn create_saved_search(&self, search: &SavedSearch) -> AppResult<()> {
+        self.state.lock().unwrap().saved.push(search.clone());
+        Ok(())
+    }
+
+    async fn saved_searches(&self, tenant_id: Uuid) -> AppResult<Vec<SavedSearch>> {
+        let state = self.state.lock().unwrap();
+        Ok(state.saved.iter().filter(|s| s.tenant_id == tenant_id).cloned().collect())
+    }
+
+    async fn saved_search(&self, tenant_id: Uuid, id: Uuid) -> AppResult<Option<SavedSearch>> {
+        let state = self.state.lock().unwrap();
+        Ok(state.saved.iter().find(|s| s.tenant_id == tenant_id && s.id == id).cloned())
+    }
+
+    async fn delete_saved_search(&self, tenant_id: Uuid, id: Uuid) -> AppResult<bool> {
+        let mut state = self.state.lock().unwrap();
+        let before = state.saved.len();
+        state.saved.retain(|s| !(s.tenant_id == tenant_id && s.id == id));
+        Ok(state.saved.len() < before)
+    }
+}
+
+// Needs a CockroachDB with the migrations applied: `cargo test --features inventory-explain`
+#[cfg(all(test, feature = "inventory-explain"))]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use sqlx::postgres::PgPoolOptions;
+
+    async fn setup() -> PgPool {
+        let db_url = std::env::var("DATABASE_URL")
+            .unwrap_or_else(|_| "postgresql://root@localhost:26257/sirsi_test?sslmode=disable".to_string());
+        PgPoolOptions::new()
+            .max_connections(5)
+            .connect(&db_url)
+            .await
+            .expect("Failed to connect to database")
+    }
+
+    // EXPLAIN is given literal values so the plan reflects the real filter selectivity
+    async fn explain(pool: &PgPool, tenant_id: Uuid, filter: &str) -> String {
+        let sql = format!(
+            "EXPLAIN SELECT {} FROM inventory_resources WHERE tenant_id = '{}' AND {} ORDER BY id LIMIT 50",
+            RESOURCE_COLUMNS, tenant_id, filter
+        );
+        let rows: Vec<(String,)> = sqlx::query_as(&sql).fetch_all(pool).await.expect("EXPLAIN failed");
+        rows.into_iter().map(|(line,)| line).collect::<Vec<_>>().join("\n")
+    }
+
+    #[tokio::test]
+    async fn test_search_uses_inverted_indexes() {
+        let pool = setup().await;
+        let store = PgInventoryStore::new(pool.clone());
+        let tenant = Uuid::new_v4();
+        let at = Utc.with_ymd_and_hms(2025, 7, 1, 0, 0, 0).unwrap();
+        let run = DiscoveryRun {
+            id: Uuid::new_v4(),
+            tenant_id: tenant,
+            provider: "aws".into(),
+            account_id: "123456789012".into(),
+            resource_count: 0,
+            started_at: at,
+            completed_at: at,
+        };
+        let resources: Vec<DiscoveredResource> = (0..50_000)
+            .map(|i| {
+                DiscoveredResource::new("ec2_instance", &format!("i-{:08x}", i), None, "us-east-1")
+                    .with_name(Some(format!("web-{}", i)))
+                    .with_tags([("team".to_string(), format!("team-{}", i % 100))])
+            })
+            .collect();
+        store.ingest(&run, &resources).await.unwrap();
+        sqlx::query("ANALYZE inventory_resources").execute(&pool).await.unwrap();
+
+        let by_tag = InventoryQuery {
+            tags: BTreeMap::from([("team".to_string(), "team-7".to_string())]),
+            ..Default::default()
+        };
+        let plan = explain(&pool, tenant, r#"tags @> '{"team": "team-7"}'"#).await;
+        assert!(plan.contains("@inventory_resources_tags_idx"), "{}", plan);
+        let plan = explain(&pool, tenant, &format!("name ILIKE '{}'", like_pattern("web-4242"))).await;
+        assert!(plan.contains("@inventory_resources_name_trgm_idx"), "{}", plan);
+
+        let page = store.search(tenant, &by_tag, None, 1000).await.unwrap();
+        assert_eq!(page.len(), 500);
+        assert!(page.iter().all(|r| r.tags["team"] == "team-7"));
+
+        sqlx::query("DELETE FROM inventory_resources WHERE tenant_id = $1").bind(tenant).execute(&pool).await.unwrap();
+        sqlx::query("DELETE FROM inventory_accounts WHERE tenant_id = $1").bind(tenant).execute(&pool).await.unwrap();
+    }
+}
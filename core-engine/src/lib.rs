@@ -1,13 +1,33 @@
 pub mod agent;
 pub mod api;
+pub mod bastion;
+pub mod budgets;
+pub mod compliance;
 pub mod config;
 pub mod db;
+pub mod device;
+pub mod discovery;
+pub mod entitlements;
 pub mod error;
+pub mod flags;
+pub mod health;
+pub mod inventory;
+pub mod metering;
 pub mod middleware;
 pub mod models;
+pub mod orgs;
+pub mod password;
+pub mod privacy;
+pub mod provider_events;
 pub mod proto;
+pub mod remediation;
+pub mod reporting;
+pub mod retention;
 pub mod server;
 pub mod telemetry;
+pub mod warehouse;
+pub mod webhooks;
+pub mod workflow_runs;
 
 // Re-export commonly used types for easier access in tests
 pub use config::AppConfig;
@@ -3,6 +3,7 @@ mod agent;
 mod config;
 mod db;
 mod error;
+mod health;
 mod middleware;
 mod models;
 mod proto;
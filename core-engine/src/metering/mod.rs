@@ -0,0 +1,430 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use axum::async_trait;
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::error::{AppError, AppResult};
+
+pub mod store;
+
+pub use store::{InMemoryUsageStore, PgUsageStore};
+
+// Billable events, aggregated per tenant per UTC day
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "VARCHAR", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum UsageEvent {
+    ResourceCreated,
+    DiscoveryRun,
+    WorkflowExecution,
+    FunctionInvocation,
+    // Counted in minutes rather than occurrences
+    BuildMinutes,
+}
+
+impl UsageEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UsageEvent::ResourceCreated => "resource_created",
+            UsageEvent::DiscoveryRun => "discovery_run",
+            UsageEvent::WorkflowExecution => "workflow_execution",
+            UsageEvent::FunctionInvocation => "function_invocation",
+            UsageEvent::BuildMinutes => "build_minutes",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "VARCHAR", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum Quota {
+    MaxProjects,
+    MaxConcurrentWorkflowRuns,
+    MaxFunctions,
+    // Counted against plan entitlements rather than tenant quota limits
+    CloudAccounts,
+    SsoProviders,
+}
+
+impl Quota {
+    pub const ALL: &'static [Quota] = &[Quota::MaxProjects, Quota::MaxConcurrentWorkflowRuns, Quota::MaxFunctions];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Quota::MaxProjects => "max_projects",
+            Quota::MaxConcurrentWorkflowRuns => "max_concurrent_workflow_runs",
+            Quota::MaxFunctions => "max_functions",
+            Quota::CloudAccounts => "cloud_accounts",
+            Quota::SsoProviders => "sso_providers",
+        }
+    }
+}
+
+// Per-tenant ceilings; a missing value means unlimited
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuotaLimits {
+    pub max_projects: Option<i64>,
+    pub max_concurrent_workflow_runs: Option<i64>,
+    pub max_functions: Option<i64>,
+    // Per UTC calendar month, against recorded `BuildMinutes`
+    #[serde(default)]
+    pub max_monthly_build_minutes: Option<i64>,
+}
+
+impl Default for QuotaLimits {
+    fn default() -> Self {
+        Self {
+            max_projects: Some(100),
+            max_concurrent_workflow_runs: Some(25),
+            max_functions: Some(1000),
+            max_monthly_build_minutes: Some(3000),
+        }
+    }
+}
+
+impl QuotaLimits {
+    pub fn get(&self, quota: Quota) -> Option<i64> {
+        match quota {
+            Quota::MaxProjects => self.max_projects,
+            Quota::MaxConcurrentWorkflowRuns => self.max_concurrent_workflow_runs,
+            Quota::MaxFunctions => self.max_functions,
+            Quota::CloudAccounts | Quota::SsoProviders => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, sqlx::FromRow)]
+pub struct DailyUsage {
+    pub tenant_id: Uuid,
+    pub day: NaiveDate,
+    pub event: UsageEvent,
+    pub count: i64,
+}
+
+// The resources a budget or creation block covers; tags are read from `data.tags`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResourceScope {
+    Tenant,
+    Project { project_id: Uuid },
+    Tag { key: String, value: String },
+}
+
+impl ResourceScope {
+    pub fn covers(&self, project_id: Uuid, data: &Value) -> bool {
+        match self {
+            ResourceScope::Tenant => true,
+            ResourceScope::Project { project_id: scoped } => *scoped == project_id,
+            ResourceScope::Tag { key, value } => {
+                data.get("tags").and_then(|tags| tags.get(key)).and_then(Value::as_str) == Some(value.as_str())
+            }
+        }
+    }
+}
+
+// Stops new resources being created in a scope until it is lifted
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CreationBlock {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub scope: ResourceScope,
+    pub reason: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Acquire {
+    Granted { used: i64 },
+    // The holder was already counted, e.g. redeploying an existing function
+    AlreadyHeld,
+    Exceeded { used: i64 },
+}
+
+#[async_trait]
+pub trait UsageStore: Send + Sync {
+    async fn increment(&self, tenant_id: Uuid, event: UsageEvent, day: NaiveDate, count: i64) -> AppResult<()>;
+    // All tenants when `tenant_id` is `None`
+    async fn daily(&self, tenant_id: Option<Uuid>, since: NaiveDate, until: NaiveDate) -> AppResult<Vec<DailyUsage>>;
+    async fn limits(&self, tenant_id: Uuid) -> AppResult<Option<QuotaLimits>>;
+    async fn set_limits(&self, tenant_id: Uuid, limits: &QuotaLimits) -> AppResult<()>;
+    async fn used(&self, tenant_id: Uuid, quota: Quota) -> AppResult<i64>;
+    // Must never let `used` pass `limit`, however many callers race
+    async fn try_acquire(&self, tenant_id: Uuid, quota: Quota, holder: Option<&str>, limit: i64) -> AppResult<Acquire>;
+    async fn release(&self, tenant_id: Uuid, quota: Quota, holder: Option<&str>) -> AppResult<()>;
+    async fn creation_blocks(&self, tenant_id: Uuid) -> AppResult<Vec<CreationBlock>>;
+    async fn add_creation_block(&self, block: &CreationBlock) -> AppResult<()>;
+    async fn remove_creation_block(&self, tenant_id: Uuid, id: Uuid) -> AppResult<bool>;
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QuotaStatus {
+    pub quota: Quota,
+    pub limit: Option<i64>,
+    pub used: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TenantUsage {
+    pub tenant_id: Uuid,
+    pub since: NaiveDate,
+    pub until: NaiveDate,
+    pub totals: BTreeMap<UsageEvent, i64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub daily: Vec<DailyUsage>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub quotas: Vec<QuotaStatus>,
+}
+
+impl TenantUsage {
+    fn new(tenant_id: Uuid, since: NaiveDate, until: NaiveDate) -> Self {
+        Self { tenant_id, since, until, totals: BTreeMap::new(), daily: Vec::new(), quotas: Vec::new() }
+    }
+}
+
+pub struct MeteringService {
+    store: Arc<dyn UsageStore>,
+    default_limits: QuotaLimits,
+}
+
+impl MeteringService {
+    pub fn new(store: Arc<dyn UsageStore>) -> Self {
+        Self { store, default_limits: QuotaLimits::default() }
+    }
+
+    // Applied to tenants without limits of their own
+    pub fn with_default_limits(mut self, limits: QuotaLimits) -> Self {
+        self.default_limits = limits;
+        self
+    }
+
+    // A failed write is logged rather than failing the operation being metered
+    pub async fn record(&self, tenant_id: Uuid, event: UsageEvent, now: DateTime<Utc>) {
+        self.record_count(tenant_id, event, 1, now).await
+    }
+
+    // Several units at once, e.g. the minutes a finished build ran for
+    pub async fn record_count(&self, tenant_id: Uuid, event: UsageEvent, count: i64, now: DateTime<Utc>) {
+        if let Err(e) = self.store.increment(tenant_id, event, now.date_naive(), count).await {
+            warn!("Failed to meter {:?} for tenant {}: {}", event, tenant_id, e);
+        }
+    }
+
+    // Build minutes left in the current UTC month, or `None` when unlimited
+    pub async fn remaining_build_minutes(&self, tenant_id: Uuid, now: DateTime<Utc>) -> AppResult<Option<i64>> {
+        let Some(limit) = self.limits(tenant_id).await?.max_monthly_build_minutes else {
+            return Ok(None);
+        };
+        let today = now.date_naive();
+        let month_start = today.with_day(1).unwrap_or(today);
+        let used: i64 = self
+            .store
+            .daily(Some(tenant_id), month_start, today)
+            .await?
+            .iter()
+            .filter(|d| d.event == UsageEvent::BuildMinutes)
+            .map(|d| d.count)
+            .sum();
+        Ok(Some((limit - used).max(0)))
+    }
+
+    pub async fn limits(&self, tenant_id: Uuid) -> AppResult<QuotaLimits> {
+        Ok(self.store.limits(tenant_id).await?.unwrap_or(self.default_limits))
+    }
+
+    pub async fn set_limits(&self, tenant_id: Uuid, limits: QuotaLimits) -> AppResult<()> {
+        self.store.set_limits(tenant_id, &limits).await
+    }
+
+    // Takes one unit of the quota, or fails with a 403 naming the quota and current usage.
+    // A `holder` makes the acquire idempotent per holder (one unit per function name).
+    // Returns whether a new unit was taken, i.e. whether a failed create should release it.
+    pub async fn acquire(&self, tenant_id: Uuid, quota: Quota, holder: Option<&str>) -> AppResult<bool> {
+        let Some(limit) = self.limits(tenant_id).await?.get(quota) else {
+            return Ok(false);
+        };
+        match self.store.try_acquire(tenant_id, quota, holder, limit).await? {
+            Acquire::Granted { .. } => Ok(true),
+            Acquire::AlreadyHeld => Ok(false),
+            Acquire::Exceeded { used } => Err(AppError::QuotaExceeded {
+                quota: quota.as_str().to_string(),
+                limit,
+                used,
+            }),
+        }
+    }
+
+    pub async fn release(&self, tenant_id: Uuid, quota: Quota, holder: Option<&str>) -> AppResult<()> {
+        self.store.release(tenant_id, quota, holder).await
+    }
+
+    // Takes a unit against a limit the caller decided, e.g. from the tenant's plan
+    pub async fn acquire_within(&self, tenant_id: Uuid, quota: Quota, holder: Option<&str>, limit: i64) -> AppResult<Acquire> {
+        self.store.try_acquire(tenant_id, quota, holder, limit).await
+    }
+
+    pub async fn used(&self, tenant_id: Uuid, quota: Quota) -> AppResult<i64> {
+        self.store.used(tenant_id, quota).await
+    }
+
+    pub async fn creation_blocks(&self, tenant_id: Uuid) -> AppResult<Vec<CreationBlock>> {
+        self.store.creation_blocks(tenant_id).await
+    }
+
+    pub async fn block_creation(
+        &self,
+        tenant_id: Uuid,
+        scope: ResourceScope,
+        reason: impl Into<String>,
+        now: DateTime<Utc>,
+    ) -> AppResult<CreationBlock> {
+        let block = CreationBlock { id: Uuid::new_v4(), tenant_id, scope, reason: reason.into(), created_at: now };
+        self.store.add_creation_block(&block).await?;
+        info!("Blocked resource creation for tenant {} in {:?}: {}", tenant_id, block.scope, block.reason);
+        Ok(block)
+    }
+
+    pub async fn lift_creation_block(&self, tenant_id: Uuid, id: Uuid) -> AppResult<bool> {
+        self.store.remove_creation_block(tenant_id, id).await
+    }
+
+    // Fails with a 403 naming the first block that covers the new resource
+    pub async fn check_creation(&self, tenant_id: Uuid, project_id: Uuid, data: &Value) -> AppResult<()> {
+        let blocks = self.store.creation_blocks(tenant_id).await?;
+        match blocks.iter().find(|b| b.scope.covers(project_id, data)) {
+            Some(block) => Err(AppError::Forbidden(format!("Resource creation is blocked: {}", block.reason))),
+            None => Ok(()),
+        }
+    }
+
+    pub async fn usage(&self, tenant_id: Uuid, since: NaiveDate, until: NaiveDate) -> AppResult<TenantUsage> {
+        let mut usage = TenantUsage::new(tenant_id, since, until);
+        usage.daily = self.store.daily(Some(tenant_id), since, until).await?;
+        for day in &usage.daily {
+            *usage.totals.entry(day.event).or_default() += day.count;
+        }
+        let limits = self.limits(tenant_id).await?;
+        for &quota in Quota::ALL {
+            usage.quotas.push(QuotaStatus {
+                quota,
+                limit: limits.get(quota),
+                used: self.store.used(tenant_id, quota).await?,
+            });
+        }
+        Ok(usage)
+    }
+
+    // Totals for every tenant with usage in the range, for the admin view
+    pub async fn rollup(&self, since: NaiveDate, until: NaiveDate) -> AppResult<Vec<TenantUsage>> {
+        let mut tenants: BTreeMap<Uuid, TenantUsage> = BTreeMap::new();
+        for day in self.store.daily(None, since, until).await? {
+            let usage = tenants
+                .entry(day.tenant_id)
+                .or_insert_with(|| TenantUsage::new(day.tenant_id, since, until));
+            *usage.totals.entry(day.event).or_default() += day.count;
+        }
+        Ok(tenants.into_values().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{http::StatusCode, response::IntoResponse};
+    use chrono::TimeZone;
+
+    fn service(max_projects: i64) -> Arc<MeteringService> {
+        let limits = QuotaLimits {
+            max_projects: Some(max_projects),
+            max_concurrent_workflow_runs: None,
+            max_functions: Some(2),
+            max_monthly_build_minutes: Some(60),
+        };
+        Arc::new(MeteringService::new(Arc::new(InMemoryUsageStore::new())).with_default_limits(limits))
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_acquires_never_overshoot() {
+        let metering = service(10);
+        let tenant = Uuid::new_v4();
+        let attempts: Vec<_> = (0..50)
+            .map(|_| {
+                let metering = metering.clone();
+                tokio::spawn(async move { metering.acquire(tenant, Quota::MaxProjects, None).await })
+            })
+            .collect();
+        let mut granted = 0;
+        for attempt in attempts {
+            match attempt.await.unwrap() {
+                Ok(taken) => {
+                    assert!(taken);
+                    granted += 1;
+                }
+                Err(e) => {
+                    assert!(matches!(e, AppError::QuotaExceeded { limit: 10, used: 10, .. }));
+                    assert_eq!(e.into_response().status(), StatusCode::FORBIDDEN);
+                }
+            }
+        }
+        assert_eq!(granted, 10);
+
+        metering.release(tenant, Quota::MaxProjects, None).await.unwrap();
+        metering.acquire(tenant, Quota::MaxProjects, None).await.unwrap();
+        assert!(metering.acquire(tenant, Quota::MaxProjects, None).await.is_err());
+
+        // Holders count once, so redeploying an existing function is free
+        assert!(metering.acquire(tenant, Quota::MaxFunctions, Some("resize")).await.unwrap());
+        assert!(!metering.acquire(tenant, Quota::MaxFunctions, Some("resize")).await.unwrap());
+        metering.acquire(tenant, Quota::MaxFunctions, Some("thumbs")).await.unwrap();
+        assert!(metering.acquire(tenant, Quota::MaxFunctions, Some("crop")).await.is_err());
+        // Unlimited quotas are never counted
+        assert!(!metering.acquire(tenant, Quota::MaxConcurrentWorkflowRuns, None).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_daily_aggregates_and_rollup() {
+        let metering = service(10);
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let monday = Utc.with_ymd_and_hms(2025, 7, 7, 23, 59, 0).unwrap();
+        let tuesday = Utc.with_ymd_and_hms(2025, 7, 8, 0, 1, 0).unwrap();
+        metering.record(a, UsageEvent::ResourceCreated, monday).await;
+        metering.record(a, UsageEvent::ResourceCreated, tuesday).await;
+        metering.record(a, UsageEvent::FunctionInvocation, tuesday).await;
+        metering.record(b, UsageEvent::DiscoveryRun, tuesday).await;
+
+        let since = monday.date_naive();
+        let until = tuesday.date_naive();
+        let usage = metering.usage(a, since, until).await.unwrap();
+        assert_eq!(usage.daily.len(), 3);
+        assert_eq!(usage.totals[&UsageEvent::ResourceCreated], 2);
+        assert_eq!(usage.quotas.len(), Quota::ALL.len());
+
+        let rollup = metering.rollup(until, until).await.unwrap();
+        assert_eq!(rollup.len(), 2);
+        let a_total = rollup.iter().find(|u| u.tenant_id == a).unwrap();
+        assert_eq!(a_total.totals[&UsageEvent::ResourceCreated], 1);
+        assert!(a_total.daily.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_monthly_build_minutes() {
+        let metering = service(10);
+        let tenant = Uuid::new_v4();
+        let june = Utc.with_ymd_and_hms(2025, 6, 30, 22, 0, 0).unwrap();
+        let july = Utc.with_ymd_and_hms(2025, 7, 9, 12, 0, 0).unwrap();
+        metering.record_count(tenant, UsageEvent::BuildMinutes, 50, june).await;
+        metering.record_count(tenant, UsageEvent::BuildMinutes, 25, july).await;
+        metering.record_count(tenant, UsageEvent::BuildMinutes, 20, july).await;
+        assert_eq!(metering.remaining_build_minutes(tenant, july).await.unwrap(), Some(15));
+        metering.record_count(tenant, UsageEvent::BuildMinutes, 30, july).await;
+        assert_eq!(metering.remaining_build_minutes(tenant, july).await.unwrap(), Some(0));
+
+        let unlimited = QuotaLimits { max_monthly_build_minutes: None, ..QuotaLimits::default() };
+        metering.set_limits(tenant, unlimited).await.unwrap();
+        assert_eq!(metering.remaining_build_minutes(tenant, july).await.unwrap(), None);
+    }
+}
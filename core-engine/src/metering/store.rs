@@ -0,0 +1,398 @@
+use std::collections::{HashMap, HashSet};
+
+use axum::async_trait;
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use serde_json::json;
+use sqlx::types::Json;
+use sqlx::PgPool; // CockroachDB uses PostgreSQL protocol
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use super::{Acquire, CreationBlock, DailyUsage, Quota, QuotaLimits, ResourceScope, UsageEvent, UsageStore};
+use crate::error::{AppError, AppResult};
+use crate::retention::{DataCategory, ExpiredRecord, RetentionTarget};
+
+pub struct PgUsageStore {
+    pool: PgPool,
+}
+
+impl PgUsageStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl UsageStore for PgUsageStore {
+    async fn increment(&self, tenant_id: Uuid, event: UsageEvent, day: NaiveDate, count: i64) -> AppResult<()> {
+        sqlx::query(
+            r#"INSERT INTO usage_daily (tenant_id, day, event, count)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (tenant_id, day, event) DO UPDATE SET count = usage_daily.count + excluded.count"#
+        )
+        .bind(tenant_id)
+        .bind(day)
+        .bind(event)
+        .bind(count)
+        .execute(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(())
+    }
+
+    async fn daily(&self, tenant_id: Option<Uuid>, since: NaiveDate, until: NaiveDate) -> AppResult<Vec<DailyUsage>> {
+        let usage = sqlx::query_as::<_, DailyUsage>(
+            r#"SELECT tenant_id, day, event, count FROM usage_daily
+            WHERE ($1::UUID IS NULL OR tenant_id = $1) AND day >= $2 AND day <= $3
+            ORDER BY tenant_id, day, event"#
+        )
+        .bind(tenant_id)
+        .bind(since)
+        .bind(until)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(usage)
+    }
+
+    async fn limits(&self, tenant_id: Uuid) -> AppResult<Option<QuotaLimits>> {
+        let row: Option<(Option<i64>, Option<i64>, Option<i64>, Option<i64>)> = sqlx::query_as(
+            r#"SELECT max_projects, max_concurrent_workflow_runs, max_functions, max_monthly_build_minutes
+            FROM tenant_quotas WHERE tenant_id = $1"#
+        )
+        .bind(tenant_id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(row.map(|(max_projects, max_concurrent_workflow_runs, max_functions, max_monthly_build_minutes)| QuotaLimits {
+            max_projects,
+            max_concurrent_workflow_runs,
+            max_functions,
+            max_monthly_build_minutes,
+        }))
+    }
+
+    async fn set_limits(&self, tenant_id: Uuid, limits: &QuotaLimits) -> AppResult<()> {
+        sqlx::query(
+            r#"UPSERT INTO tenant_quotas
+                (tenant_id, max_projects, max_concurrent_workflow_runs, max_functions, max_monthly_build_minutes, updated_at)
+            VALUES ($1, $2, $3, $4, $5, CURRENT_TIMESTAMP)"#
+        )
+        .bind(tenant_id)
+        .bind(limits.max_projects)
+        .bind(limits.max_concurrent_workflow_runs)
+        .bind(limits.max_functions)
+        .bind(limits.max_monthly_build_minutes)
+        .execute(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(())
+    }
+
+    async fn used(&self, tenant_id: Uuid, quota: Quota) -> AppResult<i64> {
+        let used: Option<(i64,)> = sqlx::query_as("SELECT used FROM quota_usage WHERE tenant_id = $1 AND quota = $2")
+            .bind(tenant_id)
+            .bind(quota)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(used.map_or(0, |(used,)| used))
+    }
+
+    // The counter only moves through a conditional `used < limit` update on a single row, so
+    // concurrent acquires serialise on that row and the last one past the limit matches nothing
+    async fn try_acquire(&self, tenant_id: Uuid, quota: Quota, holder: Option<&str>, limit: i64) -> AppResult<Acquire> {
+        let mut tx = self.pool.begin().await.map_err(AppError::Database)?;
+        if let Some(holder) = holder {
+            let inserted = sqlx::query(
+                r#"INSERT INTO quota_holdings (tenant_id, quota, holder) VALUES ($1, $2, $3)
+                ON CONFLICT DO NOTHING"#
+            )
+            .bind(tenant_id)
+            .bind(quota)
+            .bind(holder)
+            .execute(&mut *tx)
+            .await
+            .map_err(AppError::Database)?;
+            if inserted.rows_affected() == 0 {
+                tx.commit().await.map_err(AppError::Database)?;
+                return Ok(Acquire::AlreadyHeld);
+            }
+        }
+
+        sqlx::query("INSERT INTO quota_usage (tenant_id, quota, used) VALUES ($1, $2, 0) ON CONFLICT DO NOTHING")
+            .bind(tenant_id)
+            .bind(quota)
+            .execute(&mut *tx)
+            .await
+            .map_err(AppError::Database)?;
+        let granted: Option<(i64,)> = sqlx::query_as(
+            r#"UPDATE quota_usage SET used = used + 1
+            WHERE tenant_id = $1 AND quota = $2 AND used < $3
+            RETURNING used"#
+        )
+        .bind(tenant_id)
+        .bind(quota)
+        .bind(limit)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(AppError::Database)?;
+
+        match granted {
+            Some((used,)) => {
+                tx.commit().await.map_err(AppError::Database)?;
+                Ok(Acquire::Granted { used })
+            }
+            None => {
+                tx.rollback().await.map_err(AppError::Database)?;
+                Ok(Acquire::Exceeded { used: self.used(tenant_id, quota).await? })
+            }
+        }
+    }
+
+    async fn release(&self, tenant_id: Uuid, quota: Quota, holder: Option<&str>) -> AppResult<()> {
+        let mut tx = self.pool.begin().await.map_err(AppError::Database)?;
+        if let Some(holder) = holder {
+            let removed = sqlx::query("DELETE FROM quota_holdings WHERE tenant_id = $1 AND quota = $2 AND holder = $3")
+                .bind(tenant_id)
+                .bind(quota)
+                .bind(holder)
+                .execute(&mut *tx)
+                .await
+                .map_err(AppError::Database)?;
+            if removed.rows_affected() == 0 {
+                return Ok(());
+            }
+        }
+        sqlx::query("UPDATE quota_usage SET used = used - 1 WHERE tenant_id = $1 AND quota = $2 AND used > 0")
+            .bind(tenant_id)
+            .bind(quota)
+            .execute(&mut *tx)
+            .await
+            .map_err(AppError::Database)?;
+        tx.commit().await.map_err(AppError::Database)?;
+
+        Ok(())
+    }
+
+    async fn creation_blocks(&self, tenant_id: Uuid) -> AppResult<Vec<CreationBlock>> {
+        let rows = sqlx::query_as::<_, (Uuid, Uuid, Json<ResourceScope>, String, DateTime<Utc>)>(
+            r#"SELECT id, tenant_id, scope, reason, created_at
+            FROM resource_creation_blocks WHERE tenant_id = $1 ORDER BY created_at"#
+        )
+        .bind(tenant_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(id, tenant_id, scope, reason, created_at)| CreationBlock { id, tenant_id, scope: scope.0, reason, created_at })
+            .collect())
+    }
+
+    async fn add_creation_block(&self, block: &CreationBlock) -> AppResult<()> {
+        sqlx::query(
+            r#"INSERT INTO resource_creation_blocks (id, tenant_id, scope, reason, created_at)
+            VALUES ($1, $2, $3, $4, $5)"#
+        )
+        .bind(block.id)
+        .bind(block.tenant_id)
+        .bind(Json(&block.scope))
+        .bind(&block.reason)
+        .bind(block.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(())
+    }
+
+    async fn remove_creation_block(&self, tenant_id: Uuid, id: Uuid) -> AppResult<bool> {
+        let result = sqlx::query("DELETE FROM resource_creation_blocks WHERE tenant_id = $1 AND id = $2")
+            .bind(tenant_id)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+// Daily usage is the metrics retention category; a day expires once it lies wholly before the cutoff
+#[async_trait]
+impl RetentionTarget for PgUsageStore {
+    fn category(&self) -> DataCategory {
+        DataCategory::Metrics
+    }
+
+    async fn tenants(&self) -> AppResult<Vec<Uuid>> {
+        let rows: Vec<(Uuid,)> = sqlx::query_as("SELECT DISTINCT tenant_id FROM usage_daily")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(rows.into_iter().map(|(id,)| id).collect())
+    }
+
+    async fn count_expired(&self, tenant_id: Uuid, cutoff: DateTime<Utc>) -> AppResult<u64> {
+        let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM usage_daily WHERE tenant_id = $1 AND day < $2")
+            .bind(tenant_id)
+            .bind(cutoff.date_naive())
+            .fetch_one(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(count as u64)
+    }
+
+    async fn expired(&self, tenant_id: Uuid, cutoff: DateTime<Utc>, limit: usize) -> AppResult<Vec<ExpiredRecord>> {
+        let rows: Vec<(NaiveDate, String, i64)> = sqlx::query_as(
+            r#"SELECT day, event, count FROM usage_daily
+            WHERE tenant_id = $1 AND day < $2
+            ORDER BY day, event LIMIT $3"#
+        )
+        .bind(tenant_id)
+        .bind(cutoff.date_naive())
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(day, event, count)| ExpiredRecord {
+                key: format!("{}/{}", day, event),
+                recorded_at: day.and_time(NaiveTime::MIN).and_utc(),
+                data: json!({ "tenant_id": tenant_id, "day": day, "event": event, "count": count }),
+            })
+            .collect())
+    }
+
+    async fn remove(&self, tenant_id: Uuid, records: &[ExpiredRecord]) -> AppResult<u64> {
+        let (days, events): (Vec<NaiveDate>, Vec<String>) = records
+            .iter()
+            .filter_map(|r| r.key.split_once('/'))
+            .filter_map(|(day, event)| Some((day.parse::<NaiveDate>().ok()?, event.to_string())))
+            .unzip();
+        let result = sqlx::query(
+            r#"DELETE FROM usage_daily
+            WHERE tenant_id = $1 AND (day, event) IN (SELECT * FROM unnest($2::DATE[], $3::STRING[]))"#
+        )
+        .bind(tenant_id)
+        .bind(&days)
+        .bind(&events)
+        .execute(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+#[derive(Default)]
+struct UsageState {
+    daily: HashMap<(Uuid, NaiveDate, UsageEvent), i64>,
+    limits: HashMap<Uuid, QuotaLimits>,
+    used: HashMap<(Uuid, Quota), i64>,
+    holdings: HashSet<(Uuid, Quota, String)>,
+    blocks: Vec<CreationBlock>,
+}
+
+#[derive(Default)]
+pub struct InMemoryUsageStore {
+    state: Mutex<UsageState>,
+}
+
+impl InMemoryUsageStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl UsageStore for InMemoryUsageStore {
+    async fn increment(&self, tenant_id: Uuid, event: UsageEvent, day: NaiveDate, count: i64) -> AppResult<()> {
+        *self.state.lock().await.daily.entry((tenant_id, day, event)).or_default() += count;
+        Ok(())
+    }
+
+    async fn daily(&self, tenant_id: Option<Uuid>, since: NaiveDate, until: NaiveDate) -> AppResult<Vec<DailyUsage>> {
+        let state = self.state.lock().await;
+        let mut usage: Vec<DailyUsage> = state
+            .daily
+            .iter()
+            .filter(|((tenant, day, _), _)| tenant_id.is_none_or(|t| t == *tenant) && *day >= since && *day <= until)
+            .map(|(&(tenant_id, day, event), &count)| DailyUsage { tenant_id, day, event, count })
+            .collect();
+        usage.sort_by_key(|u| (u.tenant_id, u.day, u.event));
+        Ok(usage)
+    }
+
+    async fn limits(&self, tenant_id: Uuid) -> AppResult<Option<QuotaLimits>> {
+        Ok(self.state.lock().await.limits.get(&tenant_id).copied())
+    }
+
+    async fn set_limits(&self, tenant_id: Uuid, limits: &QuotaLimits) -> AppResult<()> {
+        self.state.lock().await.limits.insert(tenant_id, *limits);
+        Ok(())
+    }
+
+    async fn used(&self, tenant_id: Uuid, quota: Quota) -> AppResult<i64> {
+        Ok(self.state.lock().await.used.get(&(tenant_id, quota)).copied().unwrap_or(0))
+    }
+
+    async fn try_acquire(&self, tenant_id: Uuid, quota: Quota, holder: Option<&str>, limit: i64) -> AppResult<Acquire> {
+        let mut state = self.state.lock().await;
+        if let Some(holder) = holder {
+            if state.holdings.contains(&(tenant_id, quota, holder.to_string())) {
+                return Ok(Acquire::AlreadyHeld);
+            }
+        }
+        let used = state.used.entry((tenant_id, quota)).or_default();
+        if *used >= limit {
+            return Ok(Acquire::Exceeded { used: *used });
+        }
+        *used += 1;
+        let used = *used;
+        if let Some(holder) = holder {
+            state.holdings.insert((tenant_id, quota, holder.to_string()));
+        }
+        Ok(Acquire::Granted { used })
+    }
+
+    async fn release(&self, tenant_id: Uuid, quota: Quota, holder: Option<&str>) -> AppResult<()> {
+        let mut state = self.state.lock().await;
+        if let Some(holder) = holder {
+            if !state.holdings.remove(&(tenant_id, quota, holder.to_string())) {
+                return Ok(());
+            }
+        }
+        if let Some(used) = state.used.get_mut(&(tenant_id, quota)) {
+            *used = (*used - 1).max(0);
+        }
+        Ok(())
+    }
+
+    async fn creation_blocks(&self, tenant_id: Uuid) -> AppResult<Vec<CreationBlock>> {
+        Ok(self.state.lock().await.blocks.iter().filter(|b| b.tenant_id == tenant_id).cloned().collect())
+    }
+
+    async fn add_creation_block(&self, block: &CreationBlock) -> AppResult<()> {
+        self.state.lock().await.blocks.push(block.clone());
+        Ok(())
+    }
+
+    async fn remove_creation_block(&self, tenant_id: Uuid, id: Uuid) -> AppResult<bool> {
+        let mut state = self.state.lock().await;
+        let before = state.blocks.len();
+        state.blocks.retain(|b| !(b.tenant_id == tenant_id && b.id == id));
+        Ok(state.blocks.len() < before)
+    }
+}
@@ -0,0 +1,339 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool; // CockroachDB uses PostgreSQL protocol
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use super::scope::Scope;
+use crate::{
+    error::{AppError, AppResult},
+    models::ApiKey,
+};
+
+const KEY_PREFIX: &str = "sirsi_";
+// last_used_at is only rewritten once per interval so busy keys don't write on every call
+const TOUCH_INTERVAL_SECS: i64 = 60;
+
+#[async_trait]
+pub trait ApiKeyStore: Send + Sync {
+    async fn insert(&self, key: &ApiKey) -> AppResult<()>;
+    async fn find_by_prefix(&self, prefix: &str) -> AppResult<Option<ApiKey>>;
+    async fn get(&self, owner_id: Uuid, id: Uuid) -> AppResult<Option<ApiKey>>;
+    async fn list(&self, owner_id: Uuid) -> AppResult<Vec<ApiKey>>;
+    async fn replace_secret(&self, owner_id: Uuid, id: Uuid, prefix: &str, key_hash: &str) -> AppResult<bool>;
+    async fn revoke(&self, owner_id: Uuid, id: Uuid, at: DateTime<Utc>) -> AppResult<bool>;
+    async fn touch(&self, id: Uuid, at: DateTime<Utc>) -> AppResult<()>;
+}
+
+pub struct PgApiKeyStore {
+    pool: PgPool,
+}
+
+impl PgApiKeyStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl ApiKeyStore for PgApiKeyStore {
+    async fn insert(&self, key: &ApiKey) -> AppResult<()> {
+        key.insert(&self.pool).await
+    }
+
+    async fn find_by_prefix(&self, prefix: &str) -> AppResult<Option<ApiKey>> {
+        ApiKey::find_by_prefix(&self.pool, prefix).await
+    }
+
+    async fn get(&self, owner_id: Uuid, id: Uuid) -> AppResult<Option<ApiKey>> {
+        ApiKey::find_by_id(&self.pool, id, owner_id).await
+    }
+
+    async fn list(&self, owner_id: Uuid) -> AppResult<Vec<ApiKey>> {
+        ApiKey::find_by_owner(&self.pool, owner_id).await
+    }
+
+    async fn replace_secret(&self, owner_id: Uuid, id: Uuid, prefix: &str, key_hash: &str) -> AppResult<bool> {
+        ApiKey::replace_secret(&self.pool, id, owner_id, prefix, key_hash).await
+    }
+
+    async fn revoke(&self, owner_id: Uuid, id: Uuid, at: DateTime<Utc>) -> AppResult<bool> {
+        ApiKey::revoke(&self.pool, id, owner_id, at).await
+    }
+
+    async fn touch(&self, id: Uuid, at: DateTime<Utc>) -> AppResult<()> {
+        ApiKey::touch(&self.pool, id, at).await
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryApiKeyStore {
+    keys: Mutex<HashMap<Uuid, ApiKey>>,
+}
+
+impl InMemoryApiKeyStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl ApiKeyStore for InMemoryApiKeyStore {
+    async fn insert(&self, key: &ApiKey) -> AppResult<()> {
+        self.keys.lock().await.insert(key.id, key.clone());
+        Ok(())
+    }
+
+    async fn find_by_prefix(&self, prefix: &str) -> AppResult<Option<ApiKey>> {
+        Ok(self.keys.lock().await.values().find(|k| k.prefix == prefix).cloned())
+    }
+
+    async fn get(&self, owner_id: Uuid, id: Uuid) -> AppResult<Option<ApiKey>> {
+        Ok(self.keys.lock().await.get(&id).filter(|k| k.owner_id == owner_id).cloned())
+    }
+
+    async fn list(&self, owner_id: Uuid) -> AppResult<Vec<ApiKey>> {
+        let mut keys: Vec<ApiKey> = self.keys.lock().await.values().filter(|k| k.owner_id == owner_id).cloned().collect();
+        keys.sort_by_key(|k| std::cmp::Reverse(k.created_at));
+        Ok(keys)
+    }
+
+    async fn replace_secret(&self, owner_id: Uuid, id: Uuid, prefix: &str, key_hash: &str) -> AppResult<bool> {
+        let mut keys = self.keys.lock().await;
+        match keys.get_mut(&id).filter(|k| k.owner_id == owner_id && k.revoked_at.is_none()) {
+            Some(key) => {
+                key.prefix = prefix.to_string();
+                key.key_hash = key_hash.to_string();
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn revoke(&self, owner_id: Uuid, id: Uuid, at: DateTime<Utc>) -> AppResult<bool> {
+        let mut keys = self.keys.lock().await;
+        match keys.get_mut(&id).filter(|k| k.owner_id == owner_id && k.revoked_at.is_none()) {
+            Some(key) => {
+                key.revoked_at = Some(at);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn touch(&self, id: Uuid, at: DateTime<Utc>) -> AppResult<()> {
+        if let Some(key) = self.keys.lock().await.get_mut(&id) {
+            key.last_used_at = Some(at);
+        }
+        Ok(())
+    }
+}
+
+fn hash_key(raw: &str) -> String {
+    format!("{:x}", Sha256::digest(raw.as_bytes()))
+}
+
+// `sirsi_<prefix>_<secret>`; only the prefix is ever stored in the clear
+fn generate_key() -> (String, String) {
+    let prefix = Uuid::new_v4().simple().to_string()[..12].to_string();
+    let secret = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+    let raw = format!("{}{}_{}", KEY_PREFIX, prefix, secret);
+    (prefix, raw)
+}
+
+fn parse_prefix(raw: &str) -> Option<&str> {
+    let (prefix, secret) = raw.strip_prefix(KEY_PREFIX)?.split_once('_')?;
+    (!prefix.is_empty() && !secret.is_empty()).then_some(prefix)
+}
+
+// Every check of a presented key goes to the store, so a revocation takes effect on the
+// next request
+pub struct ApiKeyService {
+    store: Arc<dyn ApiKeyStore>,
+}
+
+impl ApiKeyService {
+    pub fn new(store: Arc<dyn ApiKeyStore>) -> Self {
+        Self { store }
+    }
+
+    // Returns the stored key and the raw secret, which is never retrievable again
+    pub async fn issue(
+        &self,
+        owner_id: Uuid,
+        created_by: Uuid,
+        name: &str,
+        scopes: &[Scope],
+        expires_at: Option<DateTime<Utc>>,
+        now: DateTime<Utc>,
+    ) -> AppResult<(ApiKey, String)> {
+        if name.trim().is_empty() {
+            return Err(AppError::Validation("API key name must not be empty".into()));
+        }
+        if scopes.is_empty() {
+            return Err(AppError::Validation("API key needs at least one scope".into()));
+        }
+        if expires_at.is_some_and(|at| at <= now) {
+            return Err(AppError::Validation("API key expiry must be in the future".into()));
+        }
+        let (prefix, raw) = generate_key();
+        let mut scopes: Vec<String> = scopes.iter().map(|s| s.to_string()).collect();
+        scopes.sort();
+        scopes.dedup();
+        let key = ApiKey {
+            id: Uuid::new_v4(),
+            owner_id,
+            created_by,
+            name: name.trim().to_string(),
+            prefix,
+            key_hash: hash_key(&raw),
+            scopes,
+            expires_at,
+            last_used_at: None,
+            revoked_at: None,
+            created_at: now,
+        };
+        self.store.insert(&key).await?;
+        info!("Issued API key {} ({}) for {}", key.id, key.prefix, owner_id);
+        Ok((key, raw))
+    }
+
+    pub async fn authenticate(&self, raw: &str, now: DateTime<Utc>) -> AppResult<ApiKey> {
+        let invalid = || AppError::Auth("Invalid API key".into());
+        let prefix = parse_prefix(raw).ok_or_else(invalid)?;
+        let key = self.store.find_by_prefix(prefix).await?.ok_or_else(invalid)?;
+        if key.key_hash != hash_key(raw) {
+            return Err(invalid());
+        }
+        if key.revoked_at.is_some() {
+            return Err(AppError::Auth("API key has been revoked".into()));
+        }
+        if key.expires_at.is_some_and(|at| at <= now) {
+            return Err(AppError::Auth("API key has expired".into()));
+        }
+        let stale = key.last_used_at.is_none_or(|at| now - at >= Duration::seconds(TOUCH_INTERVAL_SECS));
+        if stale {
+            if let Err(e) = self.store.touch(key.id, now).await {
+                warn!("Failed to record use of API key {}: {}", key.id, e);
+            }
+        }
+        Ok(key)
+    }
+
+    pub async fn list(&self, owner_id: Uuid) -> AppResult<Vec<ApiKey>> {
+        self.store.list(owner_id).await
+    }
+
+    // Swaps in a new secret under the same id, name, scopes and expiry; the old secret
+    // stops working immediately
+    pub async fn rotate(&self, owner_id: Uuid, id: Uuid) -> AppResult<(ApiKey, String)> {
+        let (prefix, raw) = generate_key();
+        if !self.store.replace_secret(owner_id, id, &prefix, &hash_key(&raw)).await? {
+            return Err(AppError::NotFound("API key not found".into()));
+        }
+        let key = self.store.get(owner_id, id).await?.ok_or_else(|| AppError::NotFound("API key not found".into()))?;
+        info!("Rotated API key {} for {}", id, owner_id);
+        Ok((key, raw))
+    }
+
+    pub async fn revoke(&self, owner_id: Uuid, id: Uuid, now: DateTime<Utc>) -> AppResult<()> {
+        if !self.store.revoke(owner_id, id, now).await? {
+            return Err(AppError::NotFound("API key not found".into()));
+        }
+        info!("Revoked API key {} for {}", id, owner_id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::AuthUser;
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+        routing::get,
+        Extension, Router,
+    };
+    use sqlx::postgres::PgPoolOptions;
+    use tower::ServiceExt;
+
+    async fn read_handler(auth: AuthUser) -> AppResult<&'static str> {
+        auth.require(Scope::ReadResources)?;
+        Ok("read")
+    }
+
+    async fn write_handler(auth: AuthUser) -> AppResult<&'static str> {
+        auth.require(Scope::WriteResources)?;
+        Ok("written")
+    }
+
+    fn app(service: Arc<ApiKeyService>) -> Router {
+        // Never connects: API key principals don't touch the users table
+        let pool = PgPoolOptions::new().connect_lazy("postgresql://root@localhost:26257/sirsi_test").unwrap();
+        Router::new()
+            .route("/resources", get(read_handler).post(write_handler))
+            .layer(Extension(service))
+            .with_state(pool)
+    }
+
+    async fn call(app: &Router, method: &str, key: &str) -> StatusCode {
+        let request = Request::builder()
+            .method(method)
+            .uri("/resources")
+            .header("Authorization", format!("ApiKey {}", key))
+            .body(Body::empty())
+            .unwrap();
+        app.clone().oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn test_read_only_key_is_rejected_on_write_route() {
+        let service = Arc::new(ApiKeyService::new(Arc::new(InMemoryApiKeyStore::new())));
+        let owner = Uuid::new_v4();
+        let (key, raw) =
+            service.issue(owner, owner, "ci", &[Scope::ReadResources], None, Utc::now()).await.unwrap();
+        assert!(raw.starts_with(&format!("sirsi_{}_", key.prefix)));
+        assert!(!key.key_hash.contains(&raw));
+
+        let app = app(service.clone());
+        assert_eq!(call(&app, "GET", &raw).await, StatusCode::OK);
+        assert_eq!(call(&app, "POST", &raw).await, StatusCode::FORBIDDEN);
+        assert_eq!(call(&app, "GET", &format!("{}x", raw)).await, StatusCode::UNAUTHORIZED);
+
+        let listed = service.list(owner).await.unwrap();
+        assert!(listed[0].last_used_at.is_some());
+        let json = serde_json::to_value(&listed[0]).unwrap();
+        assert!(json.get("key_hash").is_none());
+        assert_eq!(json["scopes"], serde_json::json!(["read:resources"]));
+    }
+
+    #[tokio::test]
+    async fn test_revoked_and_rotated_keys_fail_immediately() {
+        let service = Arc::new(ApiKeyService::new(Arc::new(InMemoryApiKeyStore::new())));
+        let owner = Uuid::new_v4();
+        let now = Utc::now();
+        let scopes = [Scope::ReadResources, Scope::WriteResources];
+        let (key, raw) = service.issue(owner, owner, "deploy", &scopes, Some(now + Duration::days(30)), now).await.unwrap();
+        let app = app(service.clone());
+        assert_eq!(call(&app, "POST", &raw).await, StatusCode::OK);
+
+        let (rotated, new_raw) = service.rotate(owner, key.id).await.unwrap();
+        assert_eq!(rotated.scopes, key.scopes);
+        assert_eq!(call(&app, "POST", &raw).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(call(&app, "POST", &new_raw).await, StatusCode::OK);
+
+        // Another tenant can't revoke it
+        assert!(service.revoke(Uuid::new_v4(), key.id, now).await.is_err());
+        service.revoke(owner, key.id, now).await.unwrap();
+        assert_eq!(call(&app, "GET", &new_raw).await, StatusCode::UNAUTHORIZED);
+
+        let (_, expired) = service.issue(owner, owner, "old", &scopes, Some(now + Duration::seconds(1)), now).await.unwrap();
+        assert!(service.authenticate(&expired, now + Duration::seconds(2)).await.is_err());
+    }
+}
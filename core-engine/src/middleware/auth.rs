@@ -5,29 +5,120 @@ use axum::{
     response::{IntoResponse, Response},
 };
 use axum::http::header::AUTHORIZATION;
-use jsonwebtoken::{decode, DecodingKey, Validation};
+use chrono::Utc;
+use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool; // CockroachDB uses PostgreSQL protocol
+use std::sync::Arc;
 use uuid::Uuid;
 
+use super::api_key::{ApiKeyService, PgApiKeyStore};
+use super::impersonation::{ImpersonationService, PgImpersonationStore};
+use super::scope::Scope;
 use crate::{
     error::{AppError, AppResult},
-    models::user::User,
+    models::{user::User, ApiKey},
 };
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String,      // User ID
     pub exp: i64,         // Expiration time
     pub iat: i64,         // Issued at time
     pub role: String,     // User role
     pub jti: String,      // JWT ID (for token revocation)
+    // Real caller when an admin is impersonating `sub`; jti is then the session id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub act: Option<ActorClaim>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActorClaim {
+    pub sub: String,
+}
+
+// Identity behind an impersonation token
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Impersonator {
+    pub actor_id: Uuid,
+    pub session_id: Uuid,
+}
+
+pub(crate) fn jwt_secret() -> Vec<u8> {
+    std::env::var("JWT_SECRET").unwrap_or_else(|_| "your-secret-key".to_string()).into_bytes()
+}
+
+pub const ACCESS_TOKEN_TTL_HOURS: i64 = 24;
+
+// Bearer token accepted by the extractor below; returns the token and its lifetime in seconds
+pub fn issue_access_token(user_id: Uuid, role: &str) -> AppResult<(String, i64)> {
+    let now = Utc::now();
+    let ttl = chrono::Duration::hours(ACCESS_TOKEN_TTL_HOURS);
+    let claims = Claims {
+        sub: user_id.to_string(),
+        exp: (now + ttl).timestamp(),
+        iat: now.timestamp(),
+        role: role.to_string(),
+        jti: Uuid::new_v4().to_string(),
+        act: None,
+    };
+    let token = encode(&Header::default(), &claims, &EncodingKey::from_secret(&jwt_secret()))
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    Ok((token, ttl.num_seconds()))
 }
 
 #[derive(Debug)]
 pub struct AuthUser {
     pub user: User,
     pub user_id: Uuid,
+    pub scopes: Vec<Scope>,
+    // Set when the caller authenticated with an API key rather than a JWT
+    pub api_key_id: Option<Uuid>,
+    // Set while an admin is acting as `user`
+    pub impersonator: Option<Impersonator>,
+}
+
+impl AuthUser {
+    // Route guard shared by JWT and API key callers
+    pub fn require(&self, scope: Scope) -> AppResult<()> {
+        if self.scopes.contains(&scope) {
+            Ok(())
+        } else {
+            Err(AppError::Forbidden(format!("Missing required scope {}", scope)))
+        }
+    }
+
+    // Guard for actions that must never be taken on a customer's behalf, whatever the scopes
+    pub fn require_direct(&self, action: &str) -> AppResult<()> {
+        match self.impersonator {
+            Some(_) => Err(AppError::Forbidden(format!("{} is not allowed while impersonating", action))),
+            None => Ok(()),
+        }
+    }
+
+    // Who is really making the call; differs from `user_id` only while impersonating
+    pub fn actor_id(&self) -> Uuid {
+        self.impersonator.map_or(self.user_id, |i| i.actor_id)
+    }
+
+    // Synthetic principal acting for the key's tenant with only the key's scopes
+    fn from_api_key(key: ApiKey) -> Self {
+        let user = User {
+            id: key.owner_id,
+            name: format!("api-key:{}", key.name),
+            email: String::new(),
+            password_hash: String::new(),
+            created_at: None,
+            updated_at: None,
+        };
+        Self {
+            user,
+            user_id: key.owner_id,
+            scopes: key.scopes.iter().filter_map(|s| s.parse().ok()).collect(),
+            api_key_id: Some(key.id),
+            impersonator: None,
+        }
+    }
 }
 
 #[async_trait]
@@ -36,11 +127,27 @@ impl FromRequestParts<PgPool> for AuthUser
     type Rejection = Response;
 
     async fn from_request_parts(parts: &mut Parts, pool: &PgPool) -> Result<Self, Self::Rejection> {
-        // Extract the token from the Authorization header
-        let auth_header = parts
+        let header = parts
             .headers
             .get(AUTHORIZATION)
-            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.to_str().ok());
+
+        // `ApiKey <key>` from service-to-service callers
+        if let Some(raw_key) = header.and_then(|value| value.strip_prefix("ApiKey ")) {
+            let service = parts
+                .extensions
+                .get::<Arc<ApiKeyService>>()
+                .cloned()
+                .unwrap_or_else(|| Arc::new(ApiKeyService::new(Arc::new(PgApiKeyStore::new(pool.clone())))));
+            let key = service
+                .authenticate(raw_key.trim(), Utc::now())
+                .await
+                .map_err(|e| e.into_response())?;
+            return Ok(AuthUser::from_api_key(key));
+        }
+
+        // Extract the token from the Authorization header
+        let auth_header = header
             .and_then(|value| value.strip_prefix("Bearer "))
             .ok_or_else(|| AppError::Auth("Missing authorization header".into()).into_response())?;
 
@@ -51,7 +158,7 @@ impl FromRequestParts<PgPool> for AuthUser
         
         let claims = decode::<Claims>(
             auth_header,
-            &DecodingKey::from_secret(std::env::var("JWT_SECRET").unwrap_or_else(|_| "your-secret-key".to_string()).as_bytes()),
+            &DecodingKey::from_secret(&jwt_secret()),
             &validation
         )
         .map_err(|_| AppError::Auth("Invalid token".into()).into_response())?
@@ -61,13 +168,26 @@ impl FromRequestParts<PgPool> for AuthUser
         let user_id = Uuid::parse_str(&claims.sub)
             .map_err(|_| AppError::Auth("Invalid user ID".into()).into_response())?;
 
+        let impersonator = match claims.act {
+            Some(_) => {
+                let service = parts
+                    .extensions
+                    .get::<Arc<ImpersonationService>>()
+                    .cloned()
+                    .unwrap_or_else(|| Arc::new(ImpersonationService::new(Arc::new(PgImpersonationStore::new(pool.clone())))));
+                let session = service.authenticate(&claims, Utc::now()).await.map_err(|e| e.into_response())?;
+                Some(Impersonator { actor_id: session.actor_id, session_id: session.id })
+            }
+            None => None,
+        };
+
         // Get the user from the database
         let user = User::find_by_id(pool, user_id)
             .await
             .map_err(|e| e.into_response())?
             .ok_or_else(|| AppError::Auth("User not found".into()).into_response())?;
 
-        Ok(AuthUser { user, user_id })
+        Ok(AuthUser { user, user_id, scopes: Scope::for_role(&claims.role), api_key_id: None, impersonator })
     }
 }
 
@@ -79,7 +199,7 @@ pub async fn verify_token(token: &str) -> AppResult<Claims> {
     
     let claims = decode::<Claims>(
         token,
-        &DecodingKey::from_secret(std::env::var("JWT_SECRET").unwrap_or_else(|_| "your-secret-key".to_string()).as_bytes()),
+        &DecodingKey::from_secret(&jwt_secret()),
         &validation
     )
     .map_err(|_| AppError::Auth("Invalid token".into()))?
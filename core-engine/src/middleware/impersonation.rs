@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use jsonwebtoken::{encode, EncodingKey, Header};
+use sqlx::PgPool; // CockroachDB uses PostgreSQL protocol
+use tokio::sync::Mutex;
+use tracing::info;
+use uuid::Uuid;
+
+use super::auth::{jwt_secret, ActorClaim, Claims};
+use crate::{
+    error::{AppError, AppResult},
+    models::ImpersonationSession,
+};
+
+pub const DEFAULT_SESSION_MINUTES: i64 = 30;
+pub const MAX_SESSION_MINUTES: i64 = 60;
+// Impersonation tokens carry ordinary user scopes whatever the admin holds themselves
+pub const IMPERSONATION_ROLE: &str = "user";
+
+#[async_trait]
+pub trait ImpersonationStore: Send + Sync {
+    async fn insert(&self, session: &ImpersonationSession) -> AppResult<()>;
+    async fn get(&self, id: Uuid) -> AppResult<Option<ImpersonationSession>>;
+    async fn list_for_target(&self, target_id: Uuid) -> AppResult<Vec<ImpersonationSession>>;
+    async fn list_for_actor(&self, actor_id: Uuid) -> AppResult<Vec<ImpersonationSession>>;
+    async fn revoke(&self, id: Uuid, by: Uuid, at: DateTime<Utc>) -> AppResult<bool>;
+}
+
+pub struct PgImpersonationStore {
+    pool: PgPool,
+}
+
+impl PgImpersonationStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl ImpersonationStore for PgImpersonationStore {
+    async fn insert(&self, session: &ImpersonationSession) -> AppResult<()> {
+        session.insert(&self.pool).await
+    }
+
+    async fn get(&self, id: Uuid) -> AppResult<Option<ImpersonationSession>> {
+        ImpersonationSession::find_by_id(&self.pool, id).await
+    }
+
+    async fn list_for_target(&self, target_id: Uuid) -> AppResult<Vec<ImpersonationSession>> {
+        ImpersonationSession::find_by_target(&self.pool, target_id).await
+    }
+
+    async fn list_for_actor(&self, actor_id: Uuid) -> AppResult<Vec<ImpersonationSession>> {
+        ImpersonationSession::find_by_actor(&self.pool, actor_id).await
+    }
+
+    async fn revoke(&self, id: Uuid, by: Uuid, at: DateTime<Utc>) -> AppResult<bool> {
+        ImpersonationSession::revoke(&self.pool, id, by, at).await
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryImpersonationStore {
+    sessions: Mutex<HashMap<Uuid, ImpersonationSession>>,
+}
+
+impl InMemoryImpersonationStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    async fn list_where(&self, keep: impl Fn(&ImpersonationSession) -> bool) -> Vec<ImpersonationSession> {
+        let mut sessions: Vec<_> = self.sessions.lock().await.values().filter(|s| keep(s)).cloned().collect();
+        sessions.sort_by_key(|s| std::cmp::Reverse(s.started_at));
+        sessions
+    }
+}
+
+#[async_trait]
+impl ImpersonationStore for InMemoryImpersonationStore {
+    async fn insert(&self, session: &ImpersonationSession) -> AppResult<()> {
+        self.sessions.lock().await.insert(session.id, session.clone());
+        Ok(())
+    }
+
+    async fn get(&self, id: Uuid) -> AppResult<Option<ImpersonationSession>> {
+        Ok(self.sessions.lock().await.get(&id).cloned())
+    }
+
+    async fn list_for_target(&self, target_id: Uuid) -> AppResult<Vec<ImpersonationSession>> {
+        Ok(self.list_where(|s| s.target_id == target_id).await)
+    }
+
+    async fn list_for_actor(&self, actor_id: Uuid) -> AppResult<Vec<ImpersonationSession>> {
+        Ok(self.list_where(|s| s.actor_id == actor_id).await)
+    }
+
+    async fn revoke(&self, id: Uuid, by: Uuid, at: DateTime<Utc>) -> AppResult<bool> {
+        let mut sessions = self.sessions.lock().await;
+        match sessions.get_mut(&id).filter(|s| s.revoked_at.is_none()) {
+            Some(session) => {
+                session.revoked_at = Some(at);
+                session.revoked_by = Some(by);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+// Like API keys, every impersonation token is checked against its session on each request,
+// so a revocation takes effect immediately rather than at token expiry
+pub struct ImpersonationService {
+    store: Arc<dyn ImpersonationStore>,
+}
+
+impl ImpersonationService {
+    pub fn new(store: Arc<dyn ImpersonationStore>) -> Self {
+        Self { store }
+    }
+
+    // Returns the recorded session and a bearer token for the target that expires with it
+    pub async fn start(
+        &self,
+        actor_id: Uuid,
+        target_id: Uuid,
+        reason: &str,
+        duration: Option<Duration>,
+        now: DateTime<Utc>,
+    ) -> AppResult<(ImpersonationSession, String)> {
+        if reason.trim().is_empty() {
+            return Err(AppError::Validation("Impersonation needs a reason".into()));
+        }
+        if actor_id == target_id {
+            return Err(AppError::Validation("Cannot impersonate yourself".into()));
+        }
+        let duration = duration.unwrap_or_else(|| Duration::minutes(DEFAULT_SESSION_MINUTES));
+        if duration <= Duration::zero() || duration > Duration::minutes(MAX_SESSION_MINUTES) {
+            return Err(AppError::Validation(format!(
+                "Impersonation sessions last between 1 and {} minutes",
+                MAX_SESSION_MINUTES
+            )));
+        }
+
+        let session = ImpersonationSession {
+            id: Uuid::new_v4(),
+            actor_id,
+            target_id,
+            reason: reason.trim().to_string(),
+            started_at: now,
+            expires_at: now + duration,
+            revoked_at: None,
+            revoked_by: None,
+        };
+        let claims = Claims {
+            sub: target_id.to_string(),
+            exp: session.expires_at.timestamp(),
+            iat: now.timestamp(),
+            role: IMPERSONATION_ROLE.to_string(),
+            jti: session.id.to_string(),
+            act: Some(ActorClaim { sub: actor_id.to_string() }),
+        };
+        let token = encode(&Header::default(), &claims, &EncodingKey::from_secret(&jwt_secret()))
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+        self.store.insert(&session).await?;
+        info!("User {} started impersonating {} until {} (session {})", actor_id, target_id, session.expires_at, session.id);
+        Ok((session, token))
+    }
+
+    // The token's subject and actor must match the session it names
+    pub async fn authenticate(&self, claims: &Claims, now: DateTime<Utc>) -> AppResult<ImpersonationSession> {
+        let invalid = || AppError::Auth("Invalid impersonation token".into());
+        let actor = claims.act.as_ref().ok_or_else(invalid)?;
+        let id = Uuid::parse_str(&claims.jti).map_err(|_| invalid())?;
+        let session = self.store.get(id).await?.ok_or_else(invalid)?;
+        if session.target_id.to_string() != claims.sub || session.actor_id.to_string() != actor.sub {
+            return Err(invalid());
+        }
+        if session.revoked_at.is_some() {
+            return Err(AppError::Auth("Impersonation session has been revoked".into()));
+        }
+        if session.expires_at <= now {
+            return Err(AppError::Auth("Impersonation session has expired".into()));
+        }
+        Ok(session)
+    }
+
+    pub async fn list_for_target(&self, target_id: Uuid) -> AppResult<Vec<ImpersonationSession>> {
+        self.store.list_for_target(target_id).await
+    }
+
+    pub async fn list_for_actor(&self, actor_id: Uuid) -> AppResult<Vec<ImpersonationSession>> {
+        self.store.list_for_actor(actor_id).await
+    }
+
+    // Either side of a session can end it: the admin who started it or the impersonated tenant
+    pub async fn revoke(&self, id: Uuid, by: Uuid, now: DateTime<Utc>) -> AppResult<ImpersonationSession> {
+        let not_found = || AppError::NotFound("Impersonation session not found".into());
+        let session = self.store.get(id).await?.filter(|s| s.actor_id == by || s.target_id == by).ok_or_else(not_found)?;
+        if !session.is_active(now) {
+            return Err(AppError::Conflict { message: "Impersonation session has already ended".into(), current: None });
+        }
+        if !self.store.revoke(id, by, now).await? {
+            return Err(AppError::Conflict { message: "Impersonation session has already ended".into(), current: None });
+        }
+        info!("Impersonation session {} revoked by {}", id, by);
+        Ok(ImpersonationSession { revoked_at: Some(now), revoked_by: Some(by), ..session })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{decode, DecodingKey, Validation};
+
+    #[tokio::test]
+    async fn test_session_token_names_both_identities_and_stops_on_revoke() {
+        let service = ImpersonationService::new(Arc::new(InMemoryImpersonationStore::new()));
+        let (admin, customer) = (Uuid::new_v4(), Uuid::new_v4());
+        let now = Utc::now();
+        assert!(service.start(admin, customer, " ", None, now).await.is_err());
+        assert!(service.start(admin, customer, "ticket 42", Some(Duration::hours(4)), now).await.is_err());
+
+        let (session, token) = service.start(admin, customer, "ticket 42", None, now).await.unwrap();
+        assert_eq!(session.expires_at, now + Duration::minutes(DEFAULT_SESSION_MINUTES));
+        let claims = decode::<Claims>(&token, &DecodingKey::from_secret(&jwt_secret()), &Validation::default())
+            .unwrap()
+            .claims;
+        assert_eq!(claims.sub, customer.to_string());
+        assert_eq!(claims.act.as_ref().unwrap().sub, admin.to_string());
+        assert_eq!(service.authenticate(&claims, now).await.unwrap().id, session.id);
+
+        // A token re-signed for someone else doesn't ride on the session
+        let forged = Claims { sub: Uuid::new_v4().to_string(), ..claims.clone() };
+        assert!(service.authenticate(&forged, now).await.is_err());
+        assert!(service.authenticate(&claims, session.expires_at).await.is_err());
+
+        // Unrelated users can't end it; the customer can
+        assert!(service.revoke(session.id, Uuid::new_v4(), now).await.is_err());
+        let revoked = service.revoke(session.id, customer, now).await.unwrap();
+        assert_eq!(revoked.revoked_by, Some(customer));
+        assert!(service.authenticate(&claims, now).await.is_err());
+        assert_eq!(service.list_for_target(customer).await.unwrap()[0].revoked_by, Some(customer));
+    }
+}
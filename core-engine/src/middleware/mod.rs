@@ -1,3 +1,9 @@
+pub mod api_key;
 pub mod auth;
+pub mod impersonation;
+pub mod scope;
 
-pub use auth::AuthUser;
+pub use api_key::{ApiKeyService, ApiKeyStore, InMemoryApiKeyStore, PgApiKeyStore};
+pub use auth::{AuthUser, Impersonator};
+pub use impersonation::{ImpersonationService, ImpersonationStore, InMemoryImpersonationStore, PgImpersonationStore};
+pub use scope::Scope;
@@ -0,0 +1,117 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::error::AppError;
+
+// Permissions checked by route guards. JWT roles expand to a fixed set; API keys carry
+// whatever subset they were granted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Scope {
+    ReadProjects,
+    WriteProjects,
+    ReadResources,
+    WriteResources,
+    ReadAudit,
+    WriteFunctions,
+    ManageApiKeys,
+    // Cross-tenant administration: usage rollups and quota overrides
+    ManageTenants,
+    // Running scripts on fleet instances through the provider's agent
+    ExecuteCommands,
+    // Creating and changing feature flags, which take effect for every tenant
+    ManageFlags,
+    // Creating organizations and managing their members and invitations; what a caller may
+    // do inside a given org still depends on their role there
+    ManageOrgs,
+    // Registering webhook endpoints and redelivering their events
+    ManageWebhooks,
+    // Starting impersonation sessions as another tenant for support
+    Impersonate,
+    // Opening SSH sessions on fleet instances with short-lived certificates
+    StartSessions,
+}
+
+impl Scope {
+    pub const ALL: &'static [Scope] = &[
+        Scope::ReadProjects,
+        Scope::WriteProjects,
+        Scope::ReadResources,
+        Scope::WriteResources,
+        Scope::ReadAudit,
+        Scope::WriteFunctions,
+        Scope::ManageApiKeys,
+        Scope::ManageTenants,
+        Scope::ExecuteCommands,
+        Scope::ManageFlags,
+        Scope::ManageOrgs,
+        Scope::ManageWebhooks,
+        Scope::Impersonate,
+        Scope::StartSessions,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::ReadProjects => "read:projects",
+            Scope::WriteProjects => "write:projects",
+            Scope::ReadResources => "read:resources",
+            Scope::WriteResources => "write:resources",
+            Scope::ReadAudit => "read:audit",
+            Scope::WriteFunctions => "write:functions",
+            Scope::ManageApiKeys => "manage:api-keys",
+            Scope::ManageTenants => "manage:tenants",
+            Scope::ExecuteCommands => "execute:commands",
+            Scope::ManageFlags => "manage:flags",
+            Scope::ManageOrgs => "manage:orgs",
+            Scope::ManageWebhooks => "manage:webhooks",
+            Scope::Impersonate => "impersonate:users",
+            Scope::StartSessions => "start:sessions",
+        }
+    }
+
+    // Unknown roles get nothing rather than a default
+    pub fn for_role(role: &str) -> Vec<Scope> {
+        match role {
+            "admin" => Self::ALL.to_vec(),
+            "user" => Self::ALL
+                .iter()
+                .copied()
+                .filter(|scope| !matches!(scope, Scope::ManageTenants | Scope::ExecuteCommands | Scope::ManageFlags | Scope::Impersonate | Scope::StartSessions))
+                .collect(),
+            "viewer" => vec![Scope::ReadProjects, Scope::ReadResources, Scope::ReadAudit],
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Scope {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|scope| scope.as_str() == s)
+            .ok_or_else(|| AppError::InvalidInput(format!("Unknown scope '{}'", s)))
+    }
+}
+
+impl Serialize for Scope {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Scope {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
@@ -0,0 +1,123 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, postgres::PgPool}; // CockroachDB uses PostgreSQL protocol
+use uuid::Uuid;
+
+use crate::error::{Result, Error};
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ApiKey {
+    pub id: Uuid,
+    pub owner_id: Uuid,
+    pub created_by: Uuid,
+    pub name: String,
+    // Public part of the key, used for lookup and shown in listings
+    pub prefix: String,
+    #[serde(skip_serializing)]
+    pub key_hash: String,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+const COLUMNS: &str =
+    "id, owner_id, created_by, name, prefix, key_hash, scopes, expires_at, last_used_at, revoked_at, created_at";
+
+impl ApiKey {
+    pub async fn insert(&self, pool: &PgPool) -> Result<()> {
+        sqlx::query(
+            r#"INSERT INTO api_keys (id, owner_id, created_by, name, prefix, key_hash, scopes, expires_at, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"#
+        )
+        .bind(self.id)
+        .bind(self.owner_id)
+        .bind(self.created_by)
+        .bind(&self.name)
+        .bind(&self.prefix)
+        .bind(&self.key_hash)
+        .bind(&self.scopes)
+        .bind(self.expires_at)
+        .bind(self.created_at)
+        .execute(pool)
+        .await
+        .map_err(Error::Database)?;
+
+        Ok(())
+    }
+
+    pub async fn find_by_prefix(pool: &PgPool, prefix: &str) -> Result<Option<Self>> {
+        let sql = format!("SELECT {} FROM api_keys WHERE prefix = $1", COLUMNS);
+        let key = sqlx::query_as::<_, Self>(&sql)
+            .bind(prefix)
+            .fetch_optional(pool)
+            .await
+            .map_err(Error::Database)?;
+
+        Ok(key)
+    }
+
+    pub async fn find_by_id(pool: &PgPool, id: Uuid, owner_id: Uuid) -> Result<Option<Self>> {
+        let sql = format!("SELECT {} FROM api_keys WHERE id = $1 AND owner_id = $2", COLUMNS);
+        let key = sqlx::query_as::<_, Self>(&sql)
+            .bind(id)
+            .bind(owner_id)
+            .fetch_optional(pool)
+            .await
+            .map_err(Error::Database)?;
+
+        Ok(key)
+    }
+
+    pub async fn find_by_owner(pool: &PgPool, owner_id: Uuid) -> Result<Vec<Self>> {
+        let sql = format!("SELECT {} FROM api_keys WHERE owner_id = $1 ORDER BY created_at DESC", COLUMNS);
+        let keys = sqlx::query_as::<_, Self>(&sql)
+            .bind(owner_id)
+            .fetch_all(pool)
+            .await
+            .map_err(Error::Database)?;
+
+        Ok(keys)
+    }
+
+    pub async fn replace_secret(pool: &PgPool, id: Uuid, owner_id: Uuid, prefix: &str, key_hash: &str) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE api_keys SET prefix = $1, key_hash = $2 WHERE id = $3 AND owner_id = $4 AND revoked_at IS NULL"
+        )
+        .bind(prefix)
+        .bind(key_hash)
+        .bind(id)
+        .bind(owner_id)
+        .execute(pool)
+        .await
+        .map_err(Error::Database)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn revoke(pool: &PgPool, id: Uuid, owner_id: Uuid, at: DateTime<Utc>) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE api_keys SET revoked_at = $1 WHERE id = $2 AND owner_id = $3 AND revoked_at IS NULL"
+        )
+        .bind(at)
+        .bind(id)
+        .bind(owner_id)
+        .execute(pool)
+        .await
+        .map_err(Error::Database)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn touch(pool: &PgPool, id: Uuid, at: DateTime<Utc>) -> Result<()> {
+        sqlx::query("UPDATE api_keys SET last_used_at = $1 WHERE id = $2")
+            .bind(at)
+            .bind(id)
+            .execute(pool)
+            .await
+            .map_err(Error::Database)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,204 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, postgres::PgPool}; // CockroachDB uses PostgreSQL protocol
+use time::OffsetDateTime;
+use uuid::Uuid;
+use serde_json::Value;
+
+use crate::error::{Result, Error};
+use crate::retention::{DataCategory, ExpiredRecord, RetentionTarget};
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct AuditEvent {
+    pub id: Uuid,
+    pub owner_id: Uuid,
+    pub actor_id: Uuid,
+    pub action: String,
+    pub target_type: String,
+    pub target_id: Option<Uuid>,
+    pub details: Value,
+    pub created_at: Option<OffsetDateTime>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AuditFilter {
+    pub action: Option<String>,
+    pub target_type: Option<String>,
+    pub target_id: Option<Uuid>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+// Shared by every query below so listing, counting and exporting agree on what matches
+const FILTER_CLAUSE: &str = r#"owner_id = $1
+    AND ($2::STRING IS NULL OR action = $2)
+    AND ($3::STRING IS NULL OR target_type = $3)
+    AND ($4::UUID IS NULL OR target_id = $4)
+    AND ($5::TIMESTAMPTZ IS NULL OR created_at >= $5)
+    AND ($6::TIMESTAMPTZ IS NULL OR created_at < $6)"#;
+
+const COLUMNS: &str = "id, owner_id, actor_id, action, target_type, target_id, details, created_at";
+
+impl AuditEvent {
+    pub async fn record(
+        pool: &PgPool,
+        owner_id: Uuid,
+        actor_id: Uuid,
+        action: &str,
+        target_type: &str,
+        target_id: Option<Uuid>,
+        details: Value,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"INSERT INTO audit_events (owner_id, actor_id, action, target_type, target_id, details)
+            VALUES ($1, $2, $3, $4, $5, $6)"#
+        )
+        .bind(owner_id)
+        .bind(actor_id)
+        .bind(action)
+        .bind(target_type)
+        .bind(target_id)
+        .bind(details)
+        .execute(pool)
+        .await
+        .map_err(Error::Database)?;
+
+        Ok(())
+    }
+
+    pub async fn find(pool: &PgPool, owner_id: Uuid, filter: &AuditFilter, limit: i64) -> Result<Vec<Self>> {
+        let sql = format!(
+            "SELECT {} FROM audit_events WHERE {} ORDER BY created_at DESC LIMIT $7",
+            COLUMNS, FILTER_CLAUSE
+        );
+        let events = Self::bind_filter(sqlx::query_as::<_, Self>(&sql), owner_id, filter)
+            .bind(limit)
+            .fetch_all(pool)
+            .await
+            .map_err(Error::Database)?;
+
+        Ok(events)
+    }
+
+    pub async fn count(pool: &PgPool, owner_id: Uuid, filter: &AuditFilter) -> Result<i64> {
+        let sql = format!("SELECT COUNT(*) FROM audit_events WHERE {}", FILTER_CLAUSE);
+        let (count,): (i64,) = Self::bind_filter(sqlx::query_as(&sql), owner_id, filter)
+            .fetch_one(pool)
+            .await
+            .map_err(Error::Database)?;
+
+        Ok(count)
+    }
+
+    // Keyset page ordered by id, for walking the whole filtered set without an OFFSET scan
+    pub async fn page_after(
+        pool: &PgPool,
+        owner_id: Uuid,
+        filter: &AuditFilter,
+        after: Option<Uuid>,
+        limit: i64,
+    ) -> Result<Vec<Self>> {
+        let sql = format!(
+            "SELECT {} FROM audit_events WHERE {} AND ($7::UUID IS NULL OR id > $7) ORDER BY id LIMIT $8",
+            COLUMNS, FILTER_CLAUSE
+        );
+        let events = Self::bind_filter(sqlx::query_as::<_, Self>(&sql), owner_id, filter)
+            .bind(after)
+            .bind(limit)
+            .fetch_all(pool)
+            .await
+            .map_err(Error::Database)?;
+
+        Ok(events)
+    }
+
+    fn bind_filter<'q, O>(
+        query: sqlx::query::QueryAs<'q, sqlx::Postgres, O, sqlx::postgres::PgArguments>,
+        owner_id: Uuid,
+        filter: &AuditFilter,
+    ) -> sqlx::query::QueryAs<'q, sqlx::Postgres, O, sqlx::postgres::PgArguments> {
+        query
+            .bind(owner_id)
+            .bind(filter.action.clone())
+            .bind(filter.target_type.clone())
+            .bind(filter.target_id)
+            .bind(filter.since)
+            .bind(filter.until)
+    }
+}
+
+// Audit events are the audit_events retention category
+pub struct PgAuditRetention {
+    pool: PgPool,
+}
+
+impl PgAuditRetention {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[axum::async_trait]
+impl RetentionTarget for PgAuditRetention {
+    fn category(&self) -> DataCategory {
+        DataCategory::AuditEvents
+    }
+
+    async fn tenants(&self) -> Result<Vec<Uuid>> {
+        let rows: Vec<(Uuid,)> = sqlx::query_as("SELECT DISTINCT owner_id FROM audit_events")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Error::Database)?;
+
+        Ok(rows.into_iter().map(|(id,)| id).collect())
+    }
+
+    async fn count_expired(&self, tenant_id: Uuid, cutoff: DateTime<Utc>) -> Result<u64> {
+        let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM audit_events WHERE owner_id = $1 AND created_at < $2")
+            .bind(tenant_id)
+            .bind(cutoff)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(Error::Database)?;
+
+        Ok(count as u64)
+    }
+
+    async fn expired(&self, tenant_id: Uuid, cutoff: DateTime<Utc>, limit: usize) -> Result<Vec<ExpiredRecord>> {
+        let sql = format!(
+            "SELECT {} FROM audit_events WHERE owner_id = $1 AND created_at < $2 ORDER BY created_at, id LIMIT $3",
+            COLUMNS
+        );
+        let events = sqlx::query_as::<_, AuditEvent>(&sql)
+            .bind(tenant_id)
+            .bind(cutoff)
+            .bind(limit as i64)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Error::Database)?;
+
+        events
+            .into_iter()
+            .map(|event| {
+                let recorded_at = event
+                    .created_at
+                    .and_then(|t| DateTime::from_timestamp(t.unix_timestamp(), t.nanosecond()))
+                    .unwrap_or(cutoff);
+                let data = serde_json::to_value(&event).map_err(|e| Error::Serialization(e.to_string()))?;
+                Ok(ExpiredRecord { key: event.id.to_string(), recorded_at, data })
+            })
+            .collect()
+    }
+
+    async fn remove(&self, tenant_id: Uuid, records: &[ExpiredRecord]) -> Result<u64> {
+        let ids: Vec<Uuid> = records.iter().filter_map(|r| r.key.parse().ok()).collect();
+        let result = sqlx::query("DELETE FROM audit_events WHERE owner_id = $1 AND id = ANY($2)")
+            .bind(tenant_id)
+            .bind(&ids)
+            .execute(&self.pool)
+            .await
+            .map_err(Error::Database)?;
+
+        Ok(result.rows_affected())
+    }
+}
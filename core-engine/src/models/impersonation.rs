@@ -0,0 +1,93 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, postgres::PgPool}; // CockroachDB uses PostgreSQL protocol
+use uuid::Uuid;
+
+use crate::error::{Result, Error};
+
+// A support engineer acting as another tenant; the row is the consent record shown to
+// that tenant's admins
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ImpersonationSession {
+    pub id: Uuid,
+    pub actor_id: Uuid,
+    pub target_id: Uuid,
+    pub reason: String,
+    pub started_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub revoked_by: Option<Uuid>,
+}
+
+const COLUMNS: &str = "id, actor_id, target_id, reason, started_at, expires_at, revoked_at, revoked_by";
+
+impl ImpersonationSession {
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.revoked_at.is_none() && self.expires_at > now
+    }
+
+    pub async fn insert(&self, pool: &PgPool) -> Result<()> {
+        sqlx::query(
+            r#"INSERT INTO impersonation_sessions (id, actor_id, target_id, reason, started_at, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6)"#
+        )
+        .bind(self.id)
+        .bind(self.actor_id)
+        .bind(self.target_id)
+        .bind(&self.reason)
+        .bind(self.started_at)
+        .bind(self.expires_at)
+        .execute(pool)
+        .await
+        .map_err(Error::Database)?;
+
+        Ok(())
+    }
+
+    pub async fn find_by_id(pool: &PgPool, id: Uuid) -> Result<Option<Self>> {
+        let sql = format!("SELECT {} FROM impersonation_sessions WHERE id = $1", COLUMNS);
+        let session = sqlx::query_as::<_, Self>(&sql)
+            .bind(id)
+            .fetch_optional(pool)
+            .await
+            .map_err(Error::Database)?;
+
+        Ok(session)
+    }
+
+    pub async fn find_by_target(pool: &PgPool, target_id: Uuid) -> Result<Vec<Self>> {
+        let sql = format!("SELECT {} FROM impersonation_sessions WHERE target_id = $1 ORDER BY started_at DESC", COLUMNS);
+        let sessions = sqlx::query_as::<_, Self>(&sql)
+            .bind(target_id)
+            .fetch_all(pool)
+            .await
+            .map_err(Error::Database)?;
+
+        Ok(sessions)
+    }
+
+    pub async fn find_by_actor(pool: &PgPool, actor_id: Uuid) -> Result<Vec<Self>> {
+        let sql = format!("SELECT {} FROM impersonation_sessions WHERE actor_id = $1 ORDER BY started_at DESC", COLUMNS);
+        let sessions = sqlx::query_as::<_, Self>(&sql)
+            .bind(actor_id)
+            .fetch_all(pool)
+            .await
+            .map_err(Error::Database)?;
+
+        Ok(sessions)
+    }
+
+    pub async fn revoke(pool: &PgPool, id: Uuid, by: Uuid, at: DateTime<Utc>) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE impersonation_sessions SET revoked_at = $1, revoked_by = $2 WHERE id = $3 AND revoked_at IS NULL"
+        )
+        .bind(at)
+        .bind(by)
+        .bind(id)
+        .execute(pool)
+        .await
+        .map_err(Error::Database)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
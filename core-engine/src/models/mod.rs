@@ -1,5 +1,15 @@
+pub mod api_key;
+pub mod audit;
+pub mod impersonation;
 pub mod project;
 pub mod user;
 pub mod resource;
+pub mod resource_link;
+pub mod version;
 
-pub use resource::{Resource, CreateResource, UpdateResource};
+pub use api_key::ApiKey;
+pub use audit::{AuditEvent, AuditFilter, PgAuditRetention};
+pub use impersonation::ImpersonationSession;
+pub use resource::{Resource, CreateResource, UpdateResource, ResourceFilter};
+pub use resource_link::{CreateResourceLink, ImpactLevel, ResourceGraph, ResourceLink};
+pub use version::Versioned;
@@ -6,6 +6,8 @@ use validator::Validate;
 
 use crate::error::{Result, Error};
 
+use super::version::Versioned;
+
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct Project {
     pub id: Uuid,
@@ -13,6 +15,8 @@ pub struct Project {
     pub description: Option<String>,
     pub status: ProjectStatus,
     pub owner_id: Uuid,
+    pub org_id: Uuid,
+    pub version: i64,
     pub created_at: Option<OffsetDateTime>,
     pub updated_at: Option<OffsetDateTime>,
 }
@@ -32,19 +36,27 @@ pub struct CreateProject {
     pub name: String,
     pub description: Option<String>,
     pub owner_id: Uuid,
+    pub org_id: Uuid,
+}
+
+impl Versioned for Project {
+    fn version(&self) -> i64 {
+        self.version
+    }
 }
 
 impl Project {
     pub async fn create(pool: &PgPool, new_project: CreateProject) -> Result<Self> {
         let project = sqlx::query_as::<_, Self>(
-            r#"INSERT INTO projects (name, description, status, owner_id)
-            VALUES ($1, $2, $3, $4)
-            RETURNING id, name, description, status, owner_id, created_at, updated_at"#
+            r#"INSERT INTO projects (name, description, status, owner_id, org_id)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING id, name, description, status, owner_id, org_id, version, created_at, updated_at"#
         )
         .bind(&new_project.name)
         .bind(&new_project.description)
         .bind(ProjectStatus::Active)
         .bind(new_project.owner_id)
+        .bind(new_project.org_id)
         .fetch_one(pool)
         .await
         .map_err(Error::Database)?;
@@ -54,7 +66,7 @@ impl Project {
 
     pub async fn find_by_id(pool: &PgPool, id: Uuid) -> Result<Option<Self>> {
         let project = sqlx::query_as::<_, Self>(
-            r#"SELECT id, name, description, status, owner_id, created_at, updated_at
+            r#"SELECT id, name, description, status, owner_id, org_id, version, created_at, updated_at
             FROM projects WHERE id = $1"#
         )
         .bind(id)
@@ -67,7 +79,7 @@ impl Project {
 
     pub async fn find_by_owner(pool: &PgPool, owner_id: Uuid) -> Result<Vec<Self>> {
         let projects = sqlx::query_as::<_, Self>(
-            r#"SELECT id, name, description, status, owner_id, created_at, updated_at
+            r#"SELECT id, name, description, status, owner_id, org_id, version, created_at, updated_at
             FROM projects WHERE owner_id = $1
             ORDER BY created_at DESC"#
         )
@@ -79,21 +91,41 @@ impl Project {
         Ok(projects)
     }
 
-    pub async fn update(&self, pool: &PgPool) -> Result<()> {
-        sqlx::query(
-            "UPDATE projects
-            SET name = $1, description = $2, status = $3
-            WHERE id = $4"
+    // Everything in the orgs the user belongs to, whoever created it
+    pub async fn find_for_member(pool: &PgPool, user_id: Uuid) -> Result<Vec<Self>> {
+        let projects = sqlx::query_as::<_, Self>(
+            r#"SELECT p.id, p.name, p.description, p.status, p.owner_id, p.org_id, p.version, p.created_at, p.updated_at
+            FROM projects p JOIN organization_members m ON m.org_id = p.org_id
+            WHERE m.user_id = $1
+            ORDER BY p.created_at DESC"#
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await
+        .map_err(Error::Database)?;
+
+        Ok(projects)
+    }
+
+    // Only applies if the row is still at `self.version`; `None` means someone else got there first
+    pub async fn update(&self, pool: &PgPool) -> Result<Option<Self>> {
+        let project = sqlx::query_as::<_, Self>(
+            r#"UPDATE projects
+            SET name = $1, description = $2, status = $3,
+                version = version + 1, updated_at = CURRENT_TIMESTAMP
+            WHERE id = $4 AND version = $5
+            RETURNING id, name, description, status, owner_id, org_id, version, created_at, updated_at"#
         )
         .bind(&self.name)
         .bind(&self.description)
         .bind(&self.status)
         .bind(self.id)
-        .execute(pool)
+        .bind(self.version)
+        .fetch_optional(pool)
         .await
         .map_err(Error::Database)?;
 
-        Ok(())
+        Ok(project)
     }
 
     pub async fn delete(pool: &PgPool, id: Uuid) -> Result<()> {
@@ -139,6 +171,7 @@ mod tests {
             name: "Test Project".to_string(),
             description: Some("Test Description".to_string()),
             owner_id: Uuid::new_v4(),
+            org_id: Uuid::new_v4(),
         };
 
         let project = Project::create(&pool, new_project.clone())
@@ -161,9 +194,11 @@ mod tests {
         // Update
         let mut updated = found;
         updated.name = "Updated Name".to_string();
-        updated.update(&pool)
+        let saved = updated.update(&pool)
             .await
-            .expect("Failed to update project");
+            .expect("Failed to update project")
+            .expect("Update lost to a concurrent write");
+        assert_eq!(saved.version, project.version + 1);
 
         let found = Project::find_by_id(&pool, project.id)
             .await
@@ -195,6 +230,7 @@ mod tests {
                 name: format!("Test Project {}", i),
                 description: Some(format!("Test Description {}", i)),
                 owner_id,
+                org_id: Uuid::new_v4(),
             };
 
             Project::create(&pool, new_project)
@@ -207,6 +243,7 @@ mod tests {
             name: "Other Project".to_string(),
             description: Some("Other Description".to_string()),
             owner_id: Uuid::new_v4(),
+            org_id: Uuid::new_v4(),
         };
 
         Project::create(&pool, new_project)
@@ -221,4 +258,30 @@ mod tests {
         assert_eq!(projects.len(), 3);
         assert!(projects.iter().all(|p| p.owner_id == owner_id));
     }
+
+    #[tokio::test]
+    async fn test_stale_update_is_rejected() {
+        let pool = setup().await;
+        let project = Project::create(&pool, CreateProject {
+            name: "Contended".to_string(),
+            description: None,
+            owner_id: Uuid::new_v4(),
+            org_id: Uuid::new_v4(),
+        })
+        .await
+        .expect("Failed to create project");
+
+        // Two writers read the same version
+        let mut first = Project::find_by_id(&pool, project.id).await.unwrap().unwrap();
+        let mut second = Project::find_by_id(&pool, project.id).await.unwrap().unwrap();
+        first.name = "First".to_string();
+        second.name = "Second".to_string();
+
+        let won = first.update(&pool).await.unwrap().expect("First write should apply");
+        assert!(second.update(&pool).await.unwrap().is_none());
+
+        let current = Project::find_by_id(&pool, project.id).await.unwrap().unwrap();
+        assert_eq!(current.name, "First");
+        assert_eq!(current.version, won.version);
+    }
 }
@@ -1,5 +1,5 @@
 use serde::{Deserialize, Serialize};
-use sqlx::{FromRow, postgres::PgPool}; // CockroachDB uses PostgreSQL protocol
+use sqlx::{FromRow, PgExecutor, postgres::PgPool}; // CockroachDB uses PostgreSQL protocol
 use time::OffsetDateTime;
 use uuid::Uuid;
 use validator::Validate;
@@ -7,6 +7,8 @@ use serde_json::Value;
 
 use crate::error::{Result, Error};
 
+use super::version::Versioned;
+
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct Resource {
     pub id: Uuid,
@@ -16,6 +18,7 @@ pub struct Resource {
     pub data: Value,
     pub owner_id: Uuid,
     pub project_id: Uuid,
+    pub version: i64,
     pub created_at: Option<OffsetDateTime>,
     pub updated_at: Option<OffsetDateTime>,
 }
@@ -31,41 +34,60 @@ pub struct CreateResource {
     pub project_id: Uuid,
 }
 
+// Query-string filters shared by the list and export endpoints
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ResourceFilter {
+    pub project_id: Option<Uuid>,
+    pub resource_type: Option<String>,
+}
+
+const FILTER_CLAUSE: &str = r#"owner_id = $1
+    AND ($2::UUID IS NULL OR project_id = $2)
+    AND ($3::STRING IS NULL OR type = $3)"#;
+
 #[derive(Debug, Validate, Deserialize)]
 pub struct UpdateResource {
     pub name: Option<String>,
     pub resource_type: Option<String>,
     pub description: Option<String>,
     pub data: Option<Value>,
+    // Alternative to If-Match for clients that can't set headers
+    pub version: Option<i64>,
+}
+
+impl Versioned for Resource {
+    fn version(&self) -> i64 {
+        self.version
+    }
 }
 
 impl Resource {
-    pub async fn create(pool: &PgPool, new_resource: CreateResource, owner_id: Uuid) -> Result<Self> {
+    pub async fn create(executor: impl PgExecutor<'_>, new_resource: CreateResource, owner_id: Uuid) -> Result<Self> {
         let resource = sqlx::query_as::<_, Self>(
             r#"INSERT INTO resources (name, type, data, owner_id, project_id)
             VALUES ($1, $2, $3, $4, $5)
-            RETURNING id, name, type, data, owner_id, project_id, created_at, updated_at"#
+            RETURNING id, name, type, data, owner_id, project_id, version, created_at, updated_at"#
         )
         .bind(&new_resource.name)
         .bind(&new_resource.resource_type)
         .bind(&new_resource.data)
         .bind(owner_id)
         .bind(new_resource.project_id)
-        .fetch_one(pool)
+        .fetch_one(executor)
         .await
         .map_err(Error::Database)?;
 
         Ok(resource)
     }
 
-    pub async fn find_by_id(pool: &PgPool, id: Uuid, owner_id: Uuid) -> Result<Option<Self>> {
+    pub async fn find_by_id(executor: impl PgExecutor<'_>, id: Uuid, owner_id: Uuid) -> Result<Option<Self>> {
         let resource = sqlx::query_as::<_, Self>(
-            r#"SELECT id, name, type, data, owner_id, project_id, created_at, updated_at
+            r#"SELECT id, name, type, data, owner_id, project_id, version, created_at, updated_at
             FROM resources WHERE id = $1 AND owner_id = $2"#
         )
         .bind(id)
         .bind(owner_id)
-        .fetch_optional(pool)
+        .fetch_optional(executor)
         .await
         .map_err(Error::Database)?;
 
@@ -74,7 +96,7 @@ impl Resource {
 
     pub async fn find_by_owner(pool: &PgPool, owner_id: Uuid) -> Result<Vec<Self>> {
         let resources = sqlx::query_as::<_, Self>(
-            r#"SELECT id, name, type, data, owner_id, project_id, created_at, updated_at
+            r#"SELECT id, name, type, data, owner_id, project_id, version, created_at, updated_at
             FROM resources WHERE owner_id = $1
             ORDER BY created_at DESC"#
         )
@@ -86,9 +108,67 @@ impl Resource {
         Ok(resources)
     }
 
+    pub async fn find_filtered(pool: &PgPool, owner_id: Uuid, filter: &ResourceFilter) -> Result<Vec<Self>> {
+        let sql = format!(
+            r#"SELECT id, name, type, data, owner_id, project_id, version, created_at, updated_at
+            FROM resources WHERE {}
+            ORDER BY created_at DESC"#,
+            FILTER_CLAUSE
+        );
+        let resources = sqlx::query_as::<_, Self>(&sql)
+            .bind(owner_id)
+            .bind(filter.project_id)
+            .bind(filter.resource_type.clone())
+            .fetch_all(pool)
+            .await
+            .map_err(Error::Database)?;
+
+        Ok(resources)
+    }
+
+    pub async fn count_filtered(pool: &PgPool, owner_id: Uuid, filter: &ResourceFilter) -> Result<i64> {
+        let sql = format!("SELECT COUNT(*) FROM resources WHERE {}", FILTER_CLAUSE);
+        let (count,): (i64,) = sqlx::query_as(&sql)
+            .bind(owner_id)
+            .bind(filter.project_id)
+            .bind(filter.resource_type.clone())
+            .fetch_one(pool)
+            .await
+            .map_err(Error::Database)?;
+
+        Ok(count)
+    }
+
+    // Keyset page ordered by id, for walking the whole filtered set without an OFFSET scan
+    pub async fn page_after(
+        pool: &PgPool,
+        owner_id: Uuid,
+        filter: &ResourceFilter,
+        after: Option<Uuid>,
+        limit: i64,
+    ) -> Result<Vec<Self>> {
+        let sql = format!(
+            r#"SELECT id, name, type, data, owner_id, project_id, version, created_at, updated_at
+            FROM resources WHERE {} AND ($4::UUID IS NULL OR id > $4)
+            ORDER BY id LIMIT $5"#,
+            FILTER_CLAUSE
+        );
+        let resources = sqlx::query_as::<_, Self>(&sql)
+            .bind(owner_id)
+            .bind(filter.project_id)
+            .bind(filter.resource_type.clone())
+            .bind(after)
+            .bind(limit)
+            .fetch_all(pool)
+            .await
+            .map_err(Error::Database)?;
+
+        Ok(resources)
+    }
+
     pub async fn find_by_project(pool: &PgPool, project_id: Uuid, owner_id: Uuid) -> Result<Vec<Self>> {
         let resources = sqlx::query_as::<_, Self>(
-            r#"SELECT id, name, type, data, owner_id, project_id, created_at, updated_at
+            r#"SELECT id, name, type, data, owner_id, project_id, version, created_at, updated_at
             FROM resources WHERE project_id = $1 AND owner_id = $2
             ORDER BY created_at DESC"#
         )
@@ -101,34 +181,38 @@ impl Resource {
         Ok(resources)
     }
 
-    pub async fn update(&self, pool: &PgPool, updates: UpdateResource) -> Result<()> {
-        sqlx::query(
+    // Only applies if the row is still at `self.version`; `None` means someone else got there first
+    pub async fn update(&self, executor: impl PgExecutor<'_>, updates: UpdateResource) -> Result<Option<Self>> {
+        let resource = sqlx::query_as::<_, Self>(
             r#"UPDATE resources
             SET name = COALESCE($1, name),
                 type = COALESCE($2, type),
                 data = COALESCE($3, data),
+                version = version + 1,
                 updated_at = CURRENT_TIMESTAMP
-            WHERE id = $4 AND owner_id = $5"#
+            WHERE id = $4 AND owner_id = $5 AND version = $6
+            RETURNING id, name, type, data, owner_id, project_id, version, created_at, updated_at"#
         )
         .bind(updates.name)
         .bind(updates.resource_type)
         .bind(updates.data)
         .bind(self.id)
         .bind(self.owner_id)
-        .execute(pool)
+        .bind(self.version)
+        .fetch_optional(executor)
         .await
         .map_err(Error::Database)?;
 
-        Ok(())
+        Ok(resource)
     }
 
-    pub async fn delete(pool: &PgPool, id: Uuid, owner_id: Uuid) -> Result<bool> {
+    pub async fn delete(executor: impl PgExecutor<'_>, id: Uuid, owner_id: Uuid) -> Result<bool> {
         let result = sqlx::query(
             "DELETE FROM resources WHERE id = $1 AND owner_id = $2"
         )
         .bind(id)
         .bind(owner_id)
-        .execute(pool)
+        .execute(executor)
         .await
         .map_err(Error::Database)?;
 
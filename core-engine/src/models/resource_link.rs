@@ -0,0 +1,248 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgExecutor, postgres::PgPool}; // CockroachDB uses PostgreSQL protocol
+use time::OffsetDateTime;
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::error::{Result, Error};
+
+// `source` depends on `target`, so changes to the target ripple back to the source
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ResourceLink {
+    pub id: Uuid,
+    pub owner_id: Uuid,
+    pub source_id: Uuid,
+    pub target_id: Uuid,
+    pub link_type: String,
+    pub created_at: Option<OffsetDateTime>,
+}
+
+#[derive(Debug, Validate, Deserialize)]
+pub struct CreateResourceLink {
+    pub target_id: Uuid,
+    #[validate(length(min = 1, max = 100))]
+    pub link_type: String,
+}
+
+const COLUMNS: &str = "id, owner_id, source_id, target_id, link_type, created_at";
+
+impl ResourceLink {
+    // The cycle check and insert share a transaction; CockroachDB runs it serializably, so two
+    // links that only form a cycle together cannot both commit
+    pub async fn create(pool: &PgPool, owner_id: Uuid, source_id: Uuid, new_link: &CreateResourceLink) -> Result<Self> {
+        let mut tx = pool.begin().await.map_err(Error::Database)?;
+        let links = sqlx::query_as::<_, Self>(&format!("SELECT {} FROM resource_links WHERE owner_id = $1", COLUMNS))
+            .bind(owner_id)
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(Error::Database)?;
+
+        let graph = ResourceGraph::new(&links);
+        if graph.has_link(source_id, new_link.target_id, &new_link.link_type) {
+            return Err(Error::Conflict {
+                message: format!("Resource {} is already linked to {} as {}", source_id, new_link.target_id, new_link.link_type),
+                current: None,
+            });
+        }
+        if graph.would_cycle(source_id, new_link.target_id) {
+            return Err(Error::InvalidInput(format!(
+                "Linking {} to {} would create a dependency cycle",
+                source_id, new_link.target_id
+            )));
+        }
+
+        let link = sqlx::query_as::<_, Self>(&format!(
+            r#"INSERT INTO resource_links (owner_id, source_id, target_id, link_type)
+            VALUES ($1, $2, $3, $4)
+            RETURNING {}"#,
+            COLUMNS
+        ))
+        .bind(owner_id)
+        .bind(source_id)
+        .bind(new_link.target_id)
+        .bind(&new_link.link_type)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(Error::Database)?;
+        tx.commit().await.map_err(Error::Database)?;
+
+        Ok(link)
+    }
+
+    pub async fn find_by_owner(pool: &PgPool, owner_id: Uuid) -> Result<Vec<Self>> {
+        let links = sqlx::query_as::<_, Self>(&format!("SELECT {} FROM resource_links WHERE owner_id = $1", COLUMNS))
+            .bind(owner_id)
+            .fetch_all(pool)
+            .await
+            .map_err(Error::Database)?;
+
+        Ok(links)
+    }
+
+    // Links pointing at the resource, i.e. everything that depends on it directly
+    pub async fn find_inbound(pool: &PgPool, target_id: Uuid, owner_id: Uuid) -> Result<Vec<Self>> {
+        let links = sqlx::query_as::<_, Self>(&format!(
+            "SELECT {} FROM resource_links WHERE target_id = $1 AND owner_id = $2 ORDER BY created_at",
+            COLUMNS
+        ))
+        .bind(target_id)
+        .bind(owner_id)
+        .fetch_all(pool)
+        .await
+        .map_err(Error::Database)?;
+
+        Ok(links)
+    }
+
+    pub async fn delete(pool: &PgPool, id: Uuid, source_id: Uuid, owner_id: Uuid) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM resource_links WHERE id = $1 AND source_id = $2 AND owner_id = $3")
+            .bind(id)
+            .bind(source_id)
+            .bind(owner_id)
+            .execute(pool)
+            .await
+            .map_err(Error::Database)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    // Every link in or out of the resource, as they stood when removed
+    pub async fn delete_touching(executor: impl PgExecutor<'_>, resource_id: Uuid, owner_id: Uuid) -> Result<Vec<Self>> {
+        let links = sqlx::query_as::<_, Self>(&format!(
+            r#"DELETE FROM resource_links
+            WHERE (source_id = $1 OR target_id = $1) AND owner_id = $2
+            RETURNING {}"#,
+            COLUMNS
+        ))
+        .bind(resource_id)
+        .bind(owner_id)
+        .fetch_all(executor)
+        .await
+        .map_err(Error::Database)?;
+
+        Ok(links)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ImpactLevel {
+    pub depth: usize,
+    pub resource_ids: Vec<Uuid>,
+}
+
+// In-memory view of one owner's links for cycle checks and impact walks
+#[derive(Debug, Default)]
+pub struct ResourceGraph {
+    dependencies: HashMap<Uuid, Vec<(Uuid, String)>>,
+    dependents: HashMap<Uuid, Vec<Uuid>>,
+}
+
+impl ResourceGraph {
+    pub fn new(links: &[ResourceLink]) -> Self {
+        let mut graph = Self::default();
+        for link in links {
+            graph.add(link.source_id, link.target_id, &link.link_type);
+        }
+        graph
+    }
+
+    pub fn add(&mut self, source_id: Uuid, target_id: Uuid, link_type: &str) {
+        self.dependencies.entry(source_id).or_default().push((target_id, link_type.to_string()));
+        self.dependents.entry(target_id).or_default().push(source_id);
+    }
+
+    pub fn has_link(&self, source_id: Uuid, target_id: Uuid, link_type: &str) -> bool {
+        self.dependencies
+            .get(&source_id)
+            .is_some_and(|targets| targets.iter().any(|(target, kind)| *target == target_id && kind == link_type))
+    }
+
+    // A new source -> target link closes a cycle if the target already depends on the source
+    pub fn would_cycle(&self, source_id: Uuid, target_id: Uuid) -> bool {
+        let mut seen = HashSet::new();
+        let mut stack = vec![target_id];
+        while let Some(id) = stack.pop() {
+            if id == source_id {
+                return true;
+            }
+            if seen.insert(id) {
+                if let Some(targets) = self.dependencies.get(&id) {
+                    stack.extend(targets.iter().map(|(target, _)| *target));
+                }
+            }
+        }
+        false
+    }
+
+    // Everything that transitively depends on `root`, grouped by shortest distance. A resource
+    // reachable along several paths (a diamond) is listed once, at its nearest depth.
+    pub fn impact(&self, root: Uuid) -> Vec<ImpactLevel> {
+        let mut seen = HashSet::from([root]);
+        let mut levels: Vec<ImpactLevel> = Vec::new();
+        let mut queue = VecDeque::from([(root, 0usize)]);
+        while let Some((id, depth)) = queue.pop_front() {
+            for &dependent in self.dependents.get(&id).into_iter().flatten() {
+                if !seen.insert(dependent) {
+                    continue;
+                }
+                let depth = depth + 1;
+                match levels.last_mut() {
+                    Some(level) if level.depth == depth => level.resource_ids.push(dependent),
+                    _ => levels.push(ImpactLevel { depth, resource_ids: vec![dependent] }),
+                }
+                queue.push_back((dependent, depth));
+            }
+        }
+        for level in &mut levels {
+            level.resource_ids.sort();
+        }
+        levels
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_impact_groups_diamond_by_depth() {
+        let (vpc, sg_web, sg_db, instance, alarm) =
+            (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let mut graph = ResourceGraph::default();
+        graph.add(sg_web, vpc, "member_of");
+        graph.add(sg_db, vpc, "member_of");
+        // The instance reaches the VPC through both security groups
+        graph.add(instance, sg_web, "uses");
+        graph.add(instance, sg_db, "uses");
+        graph.add(alarm, instance, "monitors");
+
+        let mut security_groups = vec![sg_web, sg_db];
+        security_groups.sort();
+        assert_eq!(
+            graph.impact(vpc),
+            vec![
+                ImpactLevel { depth: 1, resource_ids: security_groups },
+                ImpactLevel { depth: 2, resource_ids: vec![instance] },
+                ImpactLevel { depth: 3, resource_ids: vec![alarm] },
+            ]
+        );
+        assert_eq!(graph.impact(sg_db).len(), 2);
+        assert!(graph.impact(alarm).is_empty());
+    }
+
+    #[test]
+    fn test_cycle_is_rejected() {
+        let (vpc, sg, instance) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let mut graph = ResourceGraph::default();
+        graph.add(sg, vpc, "member_of");
+        graph.add(instance, sg, "uses");
+
+        assert!(graph.would_cycle(vpc, instance));
+        assert!(graph.would_cycle(sg, sg));
+        assert!(!graph.would_cycle(instance, vpc));
+        assert!(graph.has_link(instance, sg, "uses"));
+        assert!(!graph.has_link(instance, sg, "member_of"));
+    }
+}
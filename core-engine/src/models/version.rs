@@ -0,0 +1,145 @@
+use std::future::Future;
+
+use serde::Serialize;
+
+use crate::error::{Error, Result};
+
+// Optimistic concurrency for rows carrying a `version` column. A guarded update adds
+// `AND version = <expected>` to its WHERE clause and sets `version = version + 1`, so a
+// stale write matches no rows instead of overwriting whoever got there first.
+pub trait Versioned: Serialize {
+    fn version(&self) -> i64;
+}
+
+// Quoted entity tag for a version, as sent in `ETag` and expected back in `If-Match`
+pub fn etag(version: i64) -> String {
+    format!("\"{}\"", version)
+}
+
+// Accepts `"3"`, `W/"3"` or a bare `3`
+pub fn parse_etag(value: &str) -> Option<i64> {
+    let value = value.trim();
+    let value = value.strip_prefix("W/").unwrap_or(value);
+    value.trim_matches('"').parse().ok()
+}
+
+// The version a write expects to replace, from `If-Match` or a `version` field in the body.
+// Writes that name neither are refused rather than applied blindly.
+pub fn expected_version(if_match: Option<&str>, body_version: Option<i64>) -> Result<i64> {
+    let header_version = if_match
+        .map(|value| {
+            parse_etag(value).ok_or_else(|| Error::InvalidInput(format!("Invalid If-Match value '{}'", value)))
+        })
+        .transpose()?;
+    match (header_version, body_version) {
+        (Some(header), Some(body)) if header != body => Err(Error::InvalidInput(format!(
+            "If-Match version {} does not match body version {}",
+            header, body
+        ))),
+        (Some(version), _) | (None, Some(version)) => Ok(version),
+        (None, None) => Err(Error::PreconditionRequired(
+            "Updates require an If-Match header or a version field".into(),
+        )),
+    }
+}
+
+// 409 carrying the winning representation, so the client can reapply its change without
+// another round trip
+pub fn conflict<T: Versioned>(entity: &str, current: &T) -> Error {
+    Error::Conflict {
+        message: format!(
+            "{} was modified concurrently and is now at version {}; reapply your change to `current` and retry with If-Match: {}",
+            entity,
+            current.version(),
+            etag(current.version())
+        ),
+        current: serde_json::to_value(current).ok(),
+    }
+}
+
+// Turns the result of a guarded update into the updated row, or a conflict carrying the
+// row as it is now. `current` is only awaited when the guard matched nothing.
+pub async fn settle_update<T, F>(entity: &str, updated: Option<T>, current: F) -> Result<T>
+where
+    T: Versioned,
+    F: Future<Output = Result<Option<T>>>,
+{
+    if let Some(updated) = updated {
+        return Ok(updated);
+    }
+    match current.await? {
+        Some(current) => Err(conflict(entity, &current)),
+        None => Err(Error::NotFound(format!("{} not found", entity))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{http::StatusCode, response::IntoResponse};
+    use std::sync::Mutex;
+
+    #[derive(Debug, Clone, Serialize)]
+    struct Row {
+        name: String,
+        version: i64,
+    }
+
+    impl Versioned for Row {
+        fn version(&self) -> i64 {
+            self.version
+        }
+    }
+
+    // Stand-in for `UPDATE ... WHERE version = $n RETURNING ...`
+    fn guarded_update(row: &Mutex<Row>, expected: i64, name: &str) -> Option<Row> {
+        let mut row = row.lock().unwrap();
+        (row.version == expected).then(|| {
+            row.name = name.to_string();
+            row.version += 1;
+            row.clone()
+        })
+    }
+
+    #[tokio::test]
+    async fn test_interleaved_updates_one_wins() {
+        let row = Mutex::new(Row { name: "original".into(), version: 1 });
+        // Both writers read version 1 before either writes
+        let read_a = row.lock().unwrap().version;
+        let read_b = row.lock().unwrap().version;
+
+        let first = guarded_update(&row, read_a, "from a");
+        let a = settle_update("Project", first, async { Ok(Some(row.lock().unwrap().clone())) }).await;
+        let second = guarded_update(&row, read_b, "from b");
+        let b = settle_update("Project", second, async { Ok(Some(row.lock().unwrap().clone())) }).await;
+
+        assert_eq!(a.unwrap().version, 2);
+        let err = b.unwrap_err();
+        match &err {
+            Error::Conflict { current: Some(current), message } => {
+                assert_eq!(current["name"], "from a");
+                assert_eq!(current["version"], 2);
+                assert!(message.contains("If-Match: \"2\""));
+            }
+            other => panic!("expected conflict, got {:?}", other),
+        }
+        assert_eq!(err.into_response().status(), StatusCode::CONFLICT);
+        assert_eq!(row.lock().unwrap().name, "from a");
+
+        let gone = settle_update::<Row, _>("Project", None, async { Ok(None) }).await;
+        assert!(matches!(gone, Err(Error::NotFound(_))));
+    }
+
+    #[test]
+    fn test_parse_etag() {
+        assert_eq!(parse_etag(&etag(7)), Some(7));
+        assert_eq!(parse_etag("W/\"12\""), Some(12));
+        assert_eq!(parse_etag("3"), Some(3));
+        assert_eq!(parse_etag("\"abc\""), None);
+
+        assert_eq!(expected_version(Some("\"4\""), None).unwrap(), 4);
+        assert_eq!(expected_version(None, Some(4)).unwrap(), 4);
+        assert!(matches!(expected_version(None, None), Err(Error::PreconditionRequired(_))));
+        assert!(matches!(expected_version(Some("\"4\""), Some(5)), Err(Error::InvalidInput(_))));
+    }
+}
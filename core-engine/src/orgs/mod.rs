@@ -0,0 +1,416 @@
+use std::sync::Arc;
+
+use axum::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::info;
+use uuid::Uuid;
+
+use crate::error::{AppError, AppResult};
+use crate::models::project::Project;
+
+pub mod store;
+
+pub use store::{InMemoryOrgStore, PgOrgStore};
+
+pub const INVITATION_TTL_DAYS: i64 = 7;
+
+// Organizations are the tenant: projects, their quota and billing belong to the org, and
+// users reach a project through their role in the org that owns it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Organization {
+    pub id: Uuid,
+    pub name: String,
+    // Set on the org every user gets for projects created outside any shared org
+    pub personal_for: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "VARCHAR", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum OrgRole {
+    Admin,
+    BillingAdmin,
+    Member,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrgPermission {
+    ReadProjects,
+    WriteProjects,
+    DeleteProjects,
+    TransferProjects,
+    ManageOrg,
+    ManageBilling,
+}
+
+impl OrgRole {
+    // What each role grants on the org and every project it owns:
+    //
+    //   permission          admin  billing_admin  member
+    //   read projects        yes       yes         yes
+    //   create/edit projects yes       no          yes
+    //   delete projects      yes       no          no
+    //   transfer projects    yes       no          no
+    //   members, name        yes       no          no
+    //   usage and quotas     yes       yes         no
+    pub fn grants(self, permission: OrgPermission) -> bool {
+        match self {
+            OrgRole::Admin => true,
+            OrgRole::BillingAdmin => matches!(permission, OrgPermission::ReadProjects | OrgPermission::ManageBilling),
+            OrgRole::Member => matches!(permission, OrgPermission::ReadProjects | OrgPermission::WriteProjects),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, sqlx::FromRow)]
+pub struct OrgMembership {
+    pub org_id: Uuid,
+    pub user_id: Uuid,
+    pub role: OrgRole,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct OrgInvitation {
+    pub id: Uuid,
+    pub org_id: Uuid,
+    pub email: String,
+    pub role: OrgRole,
+    #[serde(skip_serializing)]
+    pub token_hash: String,
+    pub invited_by: Uuid,
+    pub expires_at: DateTime<Utc>,
+    pub accepted_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+// Projects as the org layer sees them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProjectRef {
+    pub id: Uuid,
+    pub org_id: Uuid,
+    pub owner_id: Uuid,
+}
+
+impl From<&Project> for ProjectRef {
+    fn from(project: &Project) -> Self {
+        Self { id: project.id, org_id: project.org_id, owner_id: project.owner_id }
+    }
+}
+
+#[async_trait]
+pub trait OrgStore: Send + Sync {
+    // Inserts the org together with its first admin
+    async fn create(&self, org: &Organization, admin: &OrgMembership) -> AppResult<()>;
+    async fn get(&self, id: Uuid) -> AppResult<Option<Organization>>;
+    async fn personal_org(&self, user_id: Uuid) -> AppResult<Option<Organization>>;
+    async fn list_for_user(&self, user_id: Uuid) -> AppResult<Vec<Organization>>;
+    async fn rename(&self, id: Uuid, name: &str) -> AppResult<bool>;
+    // Refuses while the org still owns projects
+    async fn delete(&self, id: Uuid) -> AppResult<bool>;
+    async fn project_count(&self, id: Uuid) -> AppResult<i64>;
+
+    async fn membership(&self, org_id: Uuid, user_id: Uuid) -> AppResult<Option<OrgMembership>>;
+    async fn members(&self, org_id: Uuid) -> AppResult<Vec<OrgMembership>>;
+    async fn upsert_member(&self, membership: &OrgMembership) -> AppResult<()>;
+    async fn remove_member(&self, org_id: Uuid, user_id: Uuid) -> AppResult<bool>;
+
+    async fn insert_invitation(&self, invitation: &OrgInvitation) -> AppResult<()>;
+    async fn find_invitation(&self, token_hash: &str) -> AppResult<Option<OrgInvitation>>;
+    async fn accept_invitation(&self, id: Uuid, at: DateTime<Utc>) -> AppResult<bool>;
+
+    // Moves the project only if it is still in `from`; the new owner is whoever moved it
+    async fn move_project(&self, project_id: Uuid, from: Uuid, to: Uuid, owner_id: Uuid) -> AppResult<bool>;
+}
+
+fn hash_token(raw: &str) -> String {
+    format!("{:x}", Sha256::digest(raw.as_bytes()))
+}
+
+fn generate_token() -> String {
+    format!("sirsi_inv_{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple())
+}
+
+pub struct OrgService {
+    store: Arc<dyn OrgStore>,
+}
+
+impl OrgService {
+    pub fn new(store: Arc<dyn OrgStore>) -> Self {
+        Self { store }
+    }
+
+    pub async fn create(&self, name: &str, creator: Uuid, now: DateTime<Utc>) -> AppResult<Organization> {
+        self.insert(name, creator, None, now).await
+    }
+
+    async fn insert(&self, name: &str, creator: Uuid, personal_for: Option<Uuid>, now: DateTime<Utc>) -> AppResult<Organization> {
+        if name.trim().is_empty() {
+            return Err(AppError::Validation("Organization name must not be empty".into()));
+        }
+        let org = Organization { id: Uuid::new_v4(), name: name.trim().to_string(), personal_for, created_at: now };
+        let admin = OrgMembership { org_id: org.id, user_id: creator, role: OrgRole::Admin, created_at: now };
+        self.store.create(&org, &admin).await?;
+        info!("Created organization {} ({}) for {}", org.id, org.name, creator);
+        Ok(org)
+    }
+
+    // Where projects go when the caller doesn't name an org
+    pub async fn personal_org(&self, user_id: Uuid, name: &str, now: DateTime<Utc>) -> AppResult<Organization> {
+        if let Some(org) = self.store.personal_org(user_id).await? {
+            return Ok(org);
+        }
+        match self.insert(name, user_id, Some(user_id), now).await {
+            Ok(org) => Ok(org),
+            // Lost a race with a concurrent first request
+            Err(_) => self
+                .store
+                .personal_org(user_id)
+                .await?
+                .ok_or_else(|| AppError::Internal(format!("Could not create a personal organization for {}", user_id))),
+        }
+    }
+
+    pub async fn get(&self, id: Uuid) -> AppResult<Organization> {
+        self.store.get(id).await?.ok_or_else(|| AppError::NotFound("Organization not found".into()))
+    }
+
+    pub async fn list_for_user(&self, user_id: Uuid) -> AppResult<Vec<Organization>> {
+        self.store.list_for_user(user_id).await
+    }
+
+    // Non-members get the same NotFound as a missing org so ids can't be probed
+    pub async fn authorize(&self, user_id: Uuid, org_id: Uuid, permission: OrgPermission) -> AppResult<OrgMembership> {
+        let membership = self
+            .store
+            .membership(org_id, user_id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Organization not found".into()))?;
+        if !membership.role.grants(permission) {
+            return Err(AppError::Forbidden(format!("Your {:?} role in this organization does not allow that", membership.role)));
+        }
+        Ok(membership)
+    }
+
+    pub async fn authorize_project(&self, user_id: Uuid, project: ProjectRef, permission: OrgPermission) -> AppResult<()> {
+        match self.authorize(user_id, project.org_id, permission).await {
+            Err(AppError::NotFound(_)) => Err(AppError::NotFound("Project not found".into())),
+            other => other.map(|_| ()),
+        }
+    }
+
+    pub async fn rename(&self, user_id: Uuid, org_id: Uuid, name: &str) -> AppResult<Organization> {
+        self.authorize(user_id, org_id, OrgPermission::ManageOrg).await?;
+        if name.trim().is_empty() {
+            return Err(AppError::Validation("Organization name must not be empty".into()));
+        }
+        if !self.store.rename(org_id, name.trim()).await? {
+            return Err(AppError::NotFound("Organization not found".into()));
+        }
+        self.get(org_id).await
+    }
+
+    pub async fn delete(&self, user_id: Uuid, org_id: Uuid) -> AppResult<Organization> {
+        self.authorize(user_id, org_id, OrgPermission::ManageOrg).await?;
+        let org = self.get(org_id).await?;
+        let projects = self.store.project_count(org_id).await?;
+        if projects > 0 || !self.store.delete(org_id).await? {
+            return Err(AppError::Conflict {
+                message: format!("Organization still owns {} projects; transfer or delete them first", projects),
+                current: None,
+            });
+        }
+        Ok(org)
+    }
+
+    pub async fn members(&self, user_id: Uuid, org_id: Uuid) -> AppResult<Vec<OrgMembership>> {
+        self.authorize(user_id, org_id, OrgPermission::ReadProjects).await?;
+        self.store.members(org_id).await
+    }
+
+    // An org always keeps at least one admin
+    async fn ensure_other_admin(&self, org_id: Uuid, leaving: Uuid) -> AppResult<()> {
+        let admins = self.store.members(org_id).await?.into_iter().filter(|m| m.role == OrgRole::Admin && m.user_id != leaving).count();
+        if admins == 0 {
+            return Err(AppError::Conflict { message: "An organization needs at least one admin".into(), current: None });
+        }
+        Ok(())
+    }
+
+    pub async fn set_role(&self, actor: Uuid, org_id: Uuid, member: Uuid, role: OrgRole) -> AppResult<OrgMembership> {
+        self.authorize(actor, org_id, OrgPermission::ManageOrg).await?;
+        let mut membership = self
+            .store
+            .membership(org_id, member)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Member not found".into()))?;
+        if membership.role == OrgRole::Admin && role != OrgRole::Admin {
+            self.ensure_other_admin(org_id, member).await?;
+        }
+        membership.role = role;
+        self.store.upsert_member(&membership).await?;
+        Ok(membership)
+    }
+
+    // Admins can remove anyone; everyone can leave
+    pub async fn remove_member(&self, actor: Uuid, org_id: Uuid, member: Uuid) -> AppResult<OrgMembership> {
+        let permission = if actor == member { OrgPermission::ReadProjects } else { OrgPermission::ManageOrg };
+        self.authorize(actor, org_id, permission).await?;
+        let membership = self
+            .store
+            .membership(org_id, member)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Member not found".into()))?;
+        if membership.role == OrgRole::Admin {
+            self.ensure_other_admin(org_id, member).await?;
+        }
+        self.store.remove_member(org_id, member).await?;
+        Ok(membership)
+    }
+
+    // Returns the invitation and the raw token for the email; only its hash is stored
+    pub async fn invite(
+        &self,
+        actor: Uuid,
+        org_id: Uuid,
+        email: &str,
+        role: OrgRole,
+        now: DateTime<Utc>,
+    ) -> AppResult<(OrgInvitation, String)> {
+        self.authorize(actor, org_id, OrgPermission::ManageOrg).await?;
+        let email = email.trim().to_lowercase();
+        if !email.contains('@') {
+            return Err(AppError::Validation(format!("'{}' is not an email address", email)));
+        }
+        let raw = generate_token();
+        let invitation = OrgInvitation {
+            id: Uuid::new_v4(),
+            org_id,
+            email,
+            role,
+            token_hash: hash_token(&raw),
+            invited_by: actor,
+            expires_at: now + Duration::days(INVITATION_TTL_DAYS),
+            accepted_at: None,
+            created_at: now,
+        };
+        self.store.insert_invitation(&invitation).await?;
+        info!("Invited {} to organization {} as {:?}", invitation.email, org_id, role);
+        Ok((invitation, raw))
+    }
+
+    // The token only works for the address it was sent to
+    pub async fn accept(&self, user_id: Uuid, email: &str, raw: &str, now: DateTime<Utc>) -> AppResult<OrgMembership> {
+        let invalid = || AppError::NotFound("Invitation not found".into());
+        let invitation = self.store.find_invitation(&hash_token(raw.trim())).await?.ok_or_else(invalid)?;
+        if !invitation.email.eq_ignore_ascii_case(email.trim()) {
+            return Err(invalid());
+        }
+        if invitation.accepted_at.is_some() {
+            return Err(AppError::Conflict { message: "Invitation has already been used".into(), current: None });
+        }
+        if invitation.expires_at <= now {
+            return Err(AppError::Validation("Invitation has expired".into()));
+        }
+        if !self.store.accept_invitation(invitation.id, now).await? {
+            return Err(AppError::Conflict { message: "Invitation has already been used".into(), current: None });
+        }
+        // Accepting never downgrades an existing role
+        if let Some(existing) = self.store.membership(invitation.org_id, user_id).await? {
+            if existing.role == OrgRole::Admin || existing.role == invitation.role {
+                return Ok(existing);
+            }
+        }
+        let membership = OrgMembership { org_id: invitation.org_id, user_id, role: invitation.role, created_at: now };
+        self.store.upsert_member(&membership).await?;
+        Ok(membership)
+    }
+
+    // Needs transfer rights where the project is and create rights where it's going; the
+    // mover becomes the owner since the old owner may not belong to the new org
+    pub async fn transfer_project(&self, actor: Uuid, project: ProjectRef, to: Uuid) -> AppResult<ProjectRef> {
+        if project.org_id == to {
+            return Err(AppError::Validation("Project already belongs to that organization".into()));
+        }
+        self.authorize_project(actor, project, OrgPermission::TransferProjects).await?;
+        self.authorize(actor, to, OrgPermission::WriteProjects).await?;
+        if !self.store.move_project(project.id, project.org_id, to, actor).await? {
+            return Err(AppError::Conflict { message: "Project was moved or deleted concurrently".into(), current: None });
+        }
+        info!("Transferred project {} from organization {} to {} by {}", project.id, project.org_id, to, actor);
+        Ok(ProjectRef { id: project.id, org_id: to, owner_id: actor })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn setup() -> (Arc<InMemoryOrgStore>, OrgService, Organization, Uuid) {
+        let store = Arc::new(InMemoryOrgStore::new());
+        let service = OrgService::new(store.clone());
+        let admin = Uuid::new_v4();
+        let org = service.create("Acme", admin, Utc::now()).await.unwrap();
+        (store, service, org, admin)
+    }
+
+    #[tokio::test]
+    async fn test_org_roles_are_inherited_by_projects_and_govern_transfer() {
+        let (store, service, acme, admin) = setup().await;
+        let (member, billing, creator) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let now = Utc::now();
+        for (user, role) in [(member, OrgRole::Member), (billing, OrgRole::BillingAdmin), (creator, OrgRole::Member)] {
+            store.upsert_member(&OrgMembership { org_id: acme.id, user_id: user, role, created_at: now }).await.unwrap();
+        }
+        let project = store.insert_project(Uuid::new_v4(), acme.id, creator).await;
+
+        // The admin didn't create it but can edit and delete it; members edit, billing only reads
+        service.authorize_project(admin, project, OrgPermission::WriteProjects).await.unwrap();
+        service.authorize_project(admin, project, OrgPermission::DeleteProjects).await.unwrap();
+        service.authorize_project(member, project, OrgPermission::WriteProjects).await.unwrap();
+        assert!(matches!(service.authorize_project(member, project, OrgPermission::DeleteProjects).await, Err(AppError::Forbidden(_))));
+        service.authorize_project(billing, project, OrgPermission::ReadProjects).await.unwrap();
+        assert!(service.authorize_project(billing, project, OrgPermission::WriteProjects).await.is_err());
+        assert!(matches!(service.authorize_project(Uuid::new_v4(), project, OrgPermission::ReadProjects).await, Err(AppError::NotFound(_))));
+
+        // Moving needs admin on the source and membership in the destination
+        let other = service.create("Other", member, now).await.unwrap();
+        assert!(service.transfer_project(member, project, other.id).await.is_err());
+        assert!(service.transfer_project(admin, project, other.id).await.is_err());
+        store.upsert_member(&OrgMembership { org_id: other.id, user_id: admin, role: OrgRole::Member, created_at: now }).await.unwrap();
+        let moved = service.transfer_project(admin, project, other.id).await.unwrap();
+        assert_eq!((moved.org_id, moved.owner_id), (other.id, admin));
+        assert_eq!(store.project(project.id).await, Some(moved));
+        // The old org's roles no longer reach it, and a stale copy can't be moved again
+        assert!(service.authorize_project(creator, moved, OrgPermission::ReadProjects).await.is_err());
+        assert!(matches!(service.transfer_project(admin, project, other.id).await, Err(AppError::Conflict { .. })));
+        service.delete(admin, acme.id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_invitations_expire_and_are_bound_to_their_email() {
+        let (_, service, acme, admin) = setup().await;
+        let invitee = Uuid::new_v4();
+        let now = Utc::now();
+        let (invitation, token) = service.invite(admin, acme.id, "Dev@Example.com", OrgRole::Member, now).await.unwrap();
+        assert_eq!(invitation.expires_at, now + Duration::days(INVITATION_TTL_DAYS));
+        assert!(!invitation.token_hash.contains(&token));
+        assert!(service.invite(invitee, acme.id, "x@example.com", OrgRole::Admin, now).await.is_err());
+
+        let late = now + Duration::days(INVITATION_TTL_DAYS) + Duration::seconds(1);
+        assert!(matches!(service.accept(invitee, "dev@example.com", &token, late).await, Err(AppError::Validation(_))));
+        assert!(service.accept(invitee, "someone@example.com", &token, now).await.is_err());
+        let membership = service.accept(invitee, "dev@example.com", &token, now).await.unwrap();
+        assert_eq!(membership.role, OrgRole::Member);
+        assert!(matches!(service.accept(invitee, "dev@example.com", &token, now).await, Err(AppError::Conflict { .. })));
+
+        // The last admin can't be demoted or leave
+        assert!(service.set_role(admin, acme.id, admin, OrgRole::Member).await.is_err());
+        assert!(service.remove_member(admin, acme.id, admin).await.is_err());
+        service.remove_member(invitee, acme.id, invitee).await.unwrap();
+        assert_eq!(service.members(admin, acme.id).await.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,382 @@
+use std::collections::HashMap;
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool; // CockroachDB uses PostgreSQL protocol
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use super::{OrgInvitation, OrgMembership, OrgStore, Organization, ProjectRef};
+use crate::error::{AppError, AppResult};
+
+const ORG_COLUMNS: &str = "id, name, personal_for, created_at";
+const MEMBER_COLUMNS: &str = "org_id, user_id, role, created_at";
+const INVITATION_COLUMNS: &str = "id, org_id, email, role, token_hash, invited_by, expires_at, accepted_at, created_at";
+
+pub struct PgOrgStore {
+    pool: PgPool,
+}
+
+impl PgOrgStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl OrgStore for PgOrgStore {
+    async fn create(&self, org: &Organization, admin: &OrgMembership) -> AppResult<()> {
+        let mut tx = self.pool.begin().await.map_err(AppError::Database)?;
+        sqlx::query("INSERT INTO organizations (id, name, personal_for, created_at) VALUES ($1, $2, $3, $4)")
+            .bind(org.id)
+            .bind(&org.name)
+            .bind(org.personal_for)
+            .bind(org.created_at)
+            .execute(&mut *tx)
+            .await
+            .map_err(AppError::Database)?;
+        sqlx::query("INSERT INTO organization_members (org_id, user_id, role, created_at) VALUES ($1, $2, $3, $4)")
+            .bind(admin.org_id)
+            .bind(admin.user_id)
+            .bind(admin.role)
+            .bind(admin.created_at)
+            .execute(&mut *tx)
+            .await
+            .map_err(AppError::Database)?;
+        tx.commit().await.map_err(AppError::Database)?;
+        Ok(())
+    }
+
+    async fn get(&self, id: Uuid) -> AppResult<Option<Organization>> {
+        let org = sqlx::query_as::<_, Organization>(&format!("SELECT {} FROM organizations WHERE id = $1", ORG_COLUMNS))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(org)
+    }
+
+    async fn personal_org(&self, user_id: Uuid) -> AppResult<Option<Organization>> {
+        let org = sqlx::query_as::<_, Organization>(&format!("SELECT {} FROM organizations WHERE personal_for = $1", ORG_COLUMNS))
+            .bind(user_id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(org)
+    }
+
+    async fn list_for_user(&self, user_id: Uuid) -> AppResult<Vec<Organization>> {
+        let orgs = sqlx::query_as::<_, Organization>(
+            r#"SELECT o.id, o.name, o.personal_for, o.created_at
+            FROM organizations o JOIN organization_members m ON m.org_id = o.id
+            WHERE m.user_id = $1
+            ORDER BY o.name"#
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(orgs)
+    }
+
+    async fn rename(&self, id: Uuid, name: &str) -> AppResult<bool> {
+        let result = sqlx::query("UPDATE organizations SET name = $1 WHERE id = $2")
+            .bind(name)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn delete(&self, id: Uuid) -> AppResult<bool> {
+        // Members and invitations go with it (ON DELETE CASCADE)
+        let result = sqlx::query(
+            "DELETE FROM organizations WHERE id = $1 AND NOT EXISTS (SELECT 1 FROM projects WHERE org_id = $1)"
+        )
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn project_count(&self, id: Uuid) -> AppResult<i64> {
+        let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM projects WHERE org_id = $1")
+            .bind(id)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(count)
+    }
+
+    async fn membership(&self, org_id: Uuid, user_id: Uuid) -> AppResult<Option<OrgMembership>> {
+        let membership = sqlx::query_as::<_, OrgMembership>(&format!(
+            "SELECT {} FROM organization_members WHERE org_id = $1 AND user_id = $2",
+            MEMBER_COLUMNS
+        ))
+        .bind(org_id)
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(membership)
+    }
+
+    async fn members(&self, org_id: Uuid) -> AppResult<Vec<OrgMembership>> {
+        let members = sqlx::query_as::<_, OrgMembership>(&format!(
+            "SELECT {} FROM organization_members WHERE org_id = $1 ORDER BY created_at",
+            MEMBER_COLUMNS
+        ))
+        .bind(org_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(members)
+    }
+
+    async fn upsert_member(&self, membership: &OrgMembership) -> AppResult<()> {
+        sqlx::query(
+            r#"INSERT INTO organization_members (org_id, user_id, role, created_at)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (org_id, user_id) DO UPDATE SET role = excluded.role"#
+        )
+        .bind(membership.org_id)
+        .bind(membership.user_id)
+        .bind(membership.role)
+        .bind(membership.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(())
+    }
+
+    async fn remove_member(&self, org_id: Uuid, user_id: Uuid) -> AppResult<bool> {
+        let result = sqlx::query("DELETE FROM organization_members WHERE org_id = $1 AND user_id = $2")
+            .bind(org_id)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn insert_invitation(&self, invitation: &OrgInvitation) -> AppResult<()> {
+        sqlx::query(
+            r#"INSERT INTO organization_invitations (id, org_id, email, role, token_hash, invited_by, expires_at, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#
+        )
+        .bind(invitation.id)
+        .bind(invitation.org_id)
+        .bind(&invitation.email)
+        .bind(invitation.role)
+        .bind(&invitation.token_hash)
+        .bind(invitation.invited_by)
+        .bind(invitation.expires_at)
+        .bind(invitation.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(())
+    }
+
+    async fn find_invitation(&self, token_hash: &str) -> AppResult<Option<OrgInvitation>> {
+        let invitation = sqlx::query_as::<_, OrgInvitation>(&format!(
+            "SELECT {} FROM organization_invitations WHERE token_hash = $1",
+            INVITATION_COLUMNS
+        ))
+        .bind(token_hash)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(invitation)
+    }
+
+    async fn accept_invitation(&self, id: Uuid, at: DateTime<Utc>) -> AppResult<bool> {
+        let result = sqlx::query(
+            "UPDATE organization_invitations SET accepted_at = $1 WHERE id = $2 AND accepted_at IS NULL AND expires_at > $1"
+        )
+        .bind(at)
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn move_project(&self, project_id: Uuid, from: Uuid, to: Uuid, owner_id: Uuid) -> AppResult<bool> {
+        let result = sqlx::query(
+            r#"UPDATE projects
+            SET org_id = $1, owner_id = $2, version = version + 1, updated_at = CURRENT_TIMESTAMP
+            WHERE id = $3 AND org_id = $4"#
+        )
+        .bind(to)
+        .bind(owner_id)
+        .bind(project_id)
+        .bind(from)
+        .execute(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(result.rows_affected() == 1)
+    }
+}
+
+#[derive(Default)]
+struct State {
+    orgs: HashMap<Uuid, Organization>,
+    members: HashMap<(Uuid, Uuid), OrgMembership>,
+    invitations: HashMap<Uuid, OrgInvitation>,
+    projects: HashMap<Uuid, ProjectRef>,
+}
+
+#[derive(Default)]
+pub struct InMemoryOrgStore {
+    state: Mutex<State>,
+}
+
+impl InMemoryOrgStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Projects live in their own table; tests register the ones the org layer should see
+    pub async fn insert_project(&self, id: Uuid, org_id: Uuid, owner_id: Uuid) -> ProjectRef {
+        let project = ProjectRef { id, org_id, owner_id };
+        self.state.lock().await.projects.insert(id, project);
+        project
+    }
+
+    pub async fn project(&self, id: Uuid) -> Option<ProjectRef> {
+        self.state.lock().await.projects.get(&id).copied()
+    }
+}
+
+#[async_trait]
+impl OrgStore for InMemoryOrgStore {
+    async fn create(&self, org: &Organization, admin: &OrgMembership) -> AppResult<()> {
+        let mut state = self.state.lock().await;
+        if org.personal_for.is_some() && state.orgs.values().any(|o| o.personal_for == org.personal_for) {
+            return Err(AppError::Conflict { message: "Personal organization already exists".into(), current: None });
+        }
+        state.orgs.insert(org.id, org.clone());
+        state.members.insert((admin.org_id, admin.user_id), admin.clone());
+        Ok(())
+    }
+
+    async fn get(&self, id: Uuid) -> AppResult<Option<Organization>> {
+        Ok(self.state.lock().await.orgs.get(&id).cloned())
+    }
+
+    async fn personal_org(&self, user_id: Uuid) -> AppResult<Option<Organization>> {
+        Ok(self.state.lock().await.orgs.values().find(|o| o.personal_for == Some(user_id)).cloned())
+    }
+
+    async fn list_for_user(&self, user_id: Uuid) -> AppResult<Vec<Organization>> {
+        let state = self.state.lock().await;
+        let mut orgs: Vec<_> = state
+            .members
+            .values()
+            .filter(|m| m.user_id == user_id)
+            .filter_map(|m| state.orgs.get(&m.org_id).cloned())
+            .collect();
+        orgs.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(orgs)
+    }
+
+    async fn rename(&self, id: Uuid, name: &str) -> AppResult<bool> {
+        match self.state.lock().await.orgs.get_mut(&id) {
+            Some(org) => {
+                org.name = name.to_string();
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn delete(&self, id: Uuid) -> AppResult<bool> {
+        let mut state = self.state.lock().await;
+        if state.projects.values().any(|p| p.org_id == id) {
+            return Ok(false);
+        }
+        state.members.retain(|(org_id, _), _| *org_id != id);
+        state.invitations.retain(|_, invitation| invitation.org_id != id);
+        Ok(state.orgs.remove(&id).is_some())
+    }
+
+    async fn project_count(&self, id: Uuid) -> AppResult<i64> {
+        Ok(self.state.lock().await.projects.values().filter(|p| p.org_id == id).count() as i64)
+    }
+
+    async fn membership(&self, org_id: Uuid, user_id: Uuid) -> AppResult<Option<OrgMembership>> {
+        Ok(self.state.lock().await.members.get(&(org_id, user_id)).cloned())
+    }
+
+    async fn members(&self, org_id: Uuid) -> AppResult<Vec<OrgMembership>> {
+        let mut members: Vec<_> =
+            self.state.lock().await.members.values().filter(|m| m.org_id == org_id).cloned().collect();
+        members.sort_by_key(|m| m.created_at);
+        Ok(members)
+    }
+
+    async fn upsert_member(&self, membership: &OrgMembership) -> AppResult<()> {
+        let mut state = self.state.lock().await;
+        let key = (membership.org_id, membership.user_id);
+        match state.members.get_mut(&key) {
+            Some(existing) => existing.role = membership.role,
+            None => {
+                state.members.insert(key, membership.clone());
+            }
+        }
+        Ok(())
+    }
+
+    async fn remove_member(&self, org_id: Uuid, user_id: Uuid) -> AppResult<bool> {
+        Ok(self.state.lock().await.members.remove(&(org_id, user_id)).is_some())
+    }
+
+    async fn insert_invitation(&self, invitation: &OrgInvitation) -> AppResult<()> {
+        self.state.lock().await.invitations.insert(invitation.id, invitation.clone());
+        Ok(())
+    }
+
+    async fn find_invitation(&self, token_hash: &str) -> AppResult<Option<OrgInvitation>> {
+        Ok(self.state.lock().await.invitations.values().find(|i| i.token_hash == token_hash).cloned())
+    }
+
+    async fn accept_invitation(&self, id: Uuid, at: DateTime<Utc>) -> AppResult<bool> {
+        let mut state = self.state.lock().await;
+        match state.invitations.get_mut(&id).filter(|i| i.accepted_at.is_none() && i.expires_at > at) {
+            Some(invitation) => {
+                invitation.accepted_at = Some(at);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn move_project(&self, project_id: Uuid, from: Uuid, to: Uuid, owner_id: Uuid) -> AppResult<bool> {
+        let mut state = self.state.lock().await;
+        match state.projects.get_mut(&project_id).filter(|p| p.org_id == from) {
+            Some(project) => {
+                project.org_id = to;
+                project.owner_id = owner_id;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use axum::async_trait;
+
+use crate::error::AppResult;
+
+#[async_trait]
+pub trait BreachChecker: Send + Sync {
+    // How many times the password shows up in known breach corpora
+    async fn occurrences(&self, password: &str) -> AppResult<u64>;
+}
+
+// Have I Been Pwned range API: only the first five hex digits of the SHA-1 leave the process
+#[cfg(feature = "hibp")]
+pub struct HibpRangeChecker {
+    client: reqwest::Client,
+    base_url: String,
+}
+
+#[cfg(feature = "hibp")]
+impl HibpRangeChecker {
+    pub fn new() -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(3))
+            .user_agent("sirsi-core")
+            .build()
+            .unwrap_or_default();
+        Self { client, base_url: "https://api.pwnedpasswords.com/range".to_string() }
+    }
+
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+}
+
+#[cfg(feature = "hibp")]
+impl Default for HibpRangeChecker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "hibp")]
+#[async_trait]
+impl BreachChecker for HibpRangeChecker {
+    async fn occurrences(&self, password: &str) -> AppResult<u64> {
+        use crate::error::AppError;
+        use sha1::{Digest, Sha1};
+
+        let digest = format!("{:X}", Sha1::digest(password.as_bytes()));
+        let (prefix, suffix) = digest.split_at(5);
+        // Padding hides the real result count from anyone watching response sizes
+        let body = self
+            .client
+            .get(format!("{}/{}", self.base_url.trim_end_matches('/'), prefix))
+            .header("Add-Padding", "true")
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| AppError::ExternalService(format!("Breach check failed: {}", e)))?
+            .text()
+            .await
+            .map_err(|e| AppError::ExternalService(format!("Breach check failed: {}", e)))?;
+
+        Ok(body
+            .lines()
+            .filter_map(|line| line.trim().split_once(':'))
+            .find(|(candidate, _)| candidate.eq_ignore_ascii_case(suffix))
+            .and_then(|(_, count)| count.trim().parse().ok())
+            .unwrap_or(0))
+    }
+}
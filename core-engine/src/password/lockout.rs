@@ -0,0 +1,270 @@
+use std::sync::Arc;
+
+use axum::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use tracing::warn;
+
+use crate::error::{AppError, AppResult};
+
+// Same wording whether the account exists or not
+const LOCKED_MESSAGE: &str = "Too many failed sign-in attempts; try again later";
+// Keeps the exponential delay from overflowing before it reaches the lock duration
+const MAX_DELAY_DOUBLINGS: u32 = 20;
+
+// Failures past `free_attempts` double the wait before the next try; at `lock_after` the key
+// is locked outright for `lock_duration`
+#[derive(Debug, Clone)]
+pub struct LockoutPolicy {
+    free_attempts: i64,
+    lock_after: i64,
+    base_delay: Duration,
+    lock_duration: Duration,
+    reset_after: Duration,
+}
+
+impl LockoutPolicy {
+    pub fn new(free_attempts: i64, lock_after: i64) -> Self {
+        Self {
+            free_attempts,
+            lock_after,
+            base_delay: Duration::seconds(1),
+            lock_duration: Duration::minutes(15),
+            reset_after: Duration::hours(1),
+        }
+    }
+
+    pub fn for_accounts() -> Self {
+        Self::new(5, 10)
+    }
+
+    // Lets a shared office NAT mistype a few passwords without locking everyone out
+    pub fn for_addresses() -> Self {
+        Self::new(20, 100)
+    }
+
+    pub fn with_base_delay(mut self, delay: Duration) -> Self {
+        self.base_delay = delay;
+        self
+    }
+
+    pub fn with_lock_duration(mut self, duration: Duration) -> Self {
+        self.lock_duration = duration;
+        self
+    }
+
+    // Quiet period after which earlier failures are forgotten
+    pub fn with_reset_after(mut self, period: Duration) -> Self {
+        self.reset_after = period;
+        self
+    }
+
+    fn is_stale(&self, entry: &LockoutEntry, now: DateTime<Utc>) -> bool {
+        entry.locked_until.is_none_or(|until| until <= now) && now - entry.last_failure_at >= self.reset_after
+    }
+
+    // When the key may try again, if it has to wait at all
+    pub fn blocked_until(&self, entry: &LockoutEntry, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        if self.is_stale(entry, now) {
+            return None;
+        }
+        if let Some(until) = entry.locked_until {
+            return (until > now).then_some(until);
+        }
+        if entry.failures <= self.free_attempts {
+            return None;
+        }
+        let doublings = ((entry.failures - self.free_attempts - 1) as u32).min(MAX_DELAY_DOUBLINGS);
+        let delay = (self.base_delay * 2i32.pow(doublings)).min(self.lock_duration);
+        Some(entry.last_failure_at + delay).filter(|at| *at > now)
+    }
+
+    fn record_failure(&self, existing: Option<LockoutEntry>, key: &str, now: DateTime<Utc>) -> LockoutEntry {
+        let mut entry = match existing {
+            Some(entry) if !self.is_stale(&entry, now) => entry,
+            Some(entry) => LockoutEntry { failures: 0, locked_until: None, ..entry },
+            None => LockoutEntry::new(key, now),
+        };
+        // Coming out of a lock resumes the delays rather than handing out fresh free attempts
+        if entry.locked_until.is_some_and(|until| until <= now) {
+            entry.locked_until = None;
+            entry.failures = entry.failures.min(self.free_attempts);
+        }
+        entry.failures += 1;
+        entry.last_failure_at = now;
+        if entry.failures >= self.lock_after {
+            entry.locked_until = Some(now + self.lock_duration);
+        }
+        entry
+    }
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct LockoutEntry {
+    pub key: String,
+    pub failures: i64,
+    pub last_failure_at: DateTime<Utc>,
+    pub locked_until: Option<DateTime<Utc>>,
+    #[serde(skip_serializing)]
+    pub unlock_token_hash: Option<String>,
+    pub unlock_expires_at: Option<DateTime<Utc>>,
+}
+
+impl LockoutEntry {
+    fn new(key: &str, now: DateTime<Utc>) -> Self {
+        Self {
+            key: key.to_string(),
+            failures: 0,
+            last_failure_at: now,
+            locked_until: None,
+            unlock_token_hash: None,
+            unlock_expires_at: None,
+        }
+    }
+}
+
+#[async_trait]
+pub trait LockoutStore: Send + Sync {
+    async fn get(&self, key: &str) -> AppResult<Option<LockoutEntry>>;
+    async fn put(&self, entry: &LockoutEntry) -> AppResult<()>;
+    async fn delete(&self, key: &str) -> AppResult<()>;
+    async fn find_by_unlock_token(&self, token_hash: &str) -> AppResult<Option<LockoutEntry>>;
+}
+
+pub fn account_key(email: &str) -> String {
+    format!("account:{}", email.trim().to_lowercase())
+}
+
+pub fn address_key(ip: &str) -> String {
+    format!("ip:{}", ip.trim())
+}
+
+// Failed sign-ins counted per account and per client address
+pub struct LoginThrottle {
+    store: Arc<dyn LockoutStore>,
+    accounts: LockoutPolicy,
+    addresses: LockoutPolicy,
+}
+
+impl LoginThrottle {
+    pub fn new(store: Arc<dyn LockoutStore>) -> Self {
+        Self {
+            store,
+            accounts: LockoutPolicy::for_accounts(),
+            addresses: LockoutPolicy::for_addresses(),
+        }
+    }
+
+    pub fn with_account_policy(mut self, policy: LockoutPolicy) -> Self {
+        self.accounts = policy;
+        self
+    }
+
+    pub fn with_address_policy(mut self, policy: LockoutPolicy) -> Self {
+        self.addresses = policy;
+        self
+    }
+
+    fn keys<'a>(&'a self, email: &str, ip: Option<&str>) -> Vec<(String, &'a LockoutPolicy)> {
+        let mut keys = vec![(account_key(email), &self.accounts)];
+        keys.extend(ip.map(|ip| (address_key(ip), &self.addresses)));
+        keys
+    }
+
+    pub async fn check(&self, email: &str, ip: Option<&str>, now: DateTime<Utc>) -> AppResult<()> {
+        let mut retry_at: Option<DateTime<Utc>> = None;
+        for (key, policy) in self.keys(email, ip) {
+            if let Some(entry) = self.store.get(&key).await? {
+                retry_at = retry_at.max(policy.blocked_until(&entry, now));
+            }
+        }
+        match retry_at {
+            Some(at) => Err(AppError::RateLimited {
+                message: LOCKED_MESSAGE.into(),
+                retry_after_secs: ((at - now).num_milliseconds() as u64).div_ceil(1000).max(1),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    pub async fn record_failure(&self, email: &str, ip: Option<&str>, now: DateTime<Utc>) -> AppResult<()> {
+        for (key, policy) in self.keys(email, ip) {
+            let entry = policy.record_failure(self.store.get(&key).await?, &key, now);
+            if entry.locked_until == Some(now + policy.lock_duration) {
+                warn!("Locked {} after {} failed sign-in attempts", key, entry.failures);
+            }
+            self.store.put(&entry).await?;
+        }
+        Ok(())
+    }
+
+    // A successful sign-in only clears the account; the address keeps its count
+    pub async fn clear_account(&self, email: &str) -> AppResult<()> {
+        self.store.delete(&account_key(email)).await
+    }
+
+    pub async fn account_entry(&self, email: &str) -> AppResult<Option<LockoutEntry>> {
+        self.store.get(&account_key(email)).await
+    }
+
+    pub fn is_blocked(&self, entry: &LockoutEntry, now: DateTime<Utc>) -> bool {
+        self.accounts.blocked_until(entry, now).is_some()
+    }
+
+    pub(super) fn store(&self) -> &Arc<dyn LockoutStore> {
+        &self.store
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::password::InMemoryLockoutStore;
+
+    #[tokio::test]
+    async fn test_delays_double_then_lock_and_resume_after_the_lock() {
+        let policy = LockoutPolicy::new(3, 6).with_lock_duration(Duration::minutes(10));
+        let throttle = LoginThrottle::new(Arc::new(InMemoryLockoutStore::new())).with_account_policy(policy);
+        let email = "ada@example.com";
+        let start = Utc::now();
+
+        for _ in 0..3 {
+            throttle.record_failure(email, None, start).await.unwrap();
+        }
+        throttle.check(email, None, start).await.unwrap();
+
+        // Fourth failure waits 1s, fifth 2s
+        throttle.record_failure(email, None, start).await.unwrap();
+        assert!(matches!(throttle.check(email, None, start).await, Err(AppError::RateLimited { retry_after_secs: 1, .. })));
+        let t1 = start + Duration::seconds(1);
+        throttle.check(email, None, t1).await.unwrap();
+        throttle.record_failure(email, None, t1).await.unwrap();
+        assert!(matches!(throttle.check(email, None, t1).await, Err(AppError::RateLimited { retry_after_secs: 2, .. })));
+
+        let t3 = t1 + Duration::seconds(2);
+        throttle.record_failure(email, None, t3).await.unwrap();
+        assert!(matches!(throttle.check(email, None, t3).await, Err(AppError::RateLimited { retry_after_secs: 600, .. })));
+        // Case and whitespace don't get around the per-account key
+        assert!(throttle.check(" Ada@Example.com", None, t3 + Duration::minutes(9)).await.is_err());
+
+        let unlocked = t3 + Duration::minutes(10);
+        throttle.check(email, None, unlocked).await.unwrap();
+        throttle.record_failure(email, None, unlocked).await.unwrap();
+        assert!(matches!(throttle.check(email, None, unlocked).await, Err(AppError::RateLimited { retry_after_secs: 1, .. })));
+
+        throttle.clear_account(email).await.unwrap();
+        throttle.check(email, None, unlocked).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_address_key_spans_accounts() {
+        let throttle = LoginThrottle::new(Arc::new(InMemoryLockoutStore::new()))
+            .with_address_policy(LockoutPolicy::new(0, 3).with_lock_duration(Duration::minutes(5)));
+        let now = Utc::now();
+        for i in 0..3 {
+            throttle.record_failure(&format!("user{}@example.com", i), Some("203.0.113.9"), now).await.unwrap();
+        }
+        assert!(throttle.check("fresh@example.com", Some("203.0.113.9"), now).await.is_err());
+        throttle.check("fresh@example.com", Some("198.51.100.1"), now).await.unwrap();
+    }
+}
@@ -0,0 +1,498 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
+
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Algorithm, Argon2, Params, Version,
+};
+use axum::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use once_cell::sync::{Lazy, OnceCell};
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::error::{AppError, AppResult};
+use crate::models::user::User;
+
+pub mod breach;
+pub mod lockout;
+pub mod store;
+
+pub use breach::BreachChecker;
+#[cfg(feature = "hibp")]
+pub use breach::HibpRangeChecker;
+pub use lockout::{LockoutEntry, LockoutPolicy, LockoutStore, LoginThrottle};
+pub use store::{InMemoryCredentialStore, InMemoryLockoutStore, PgCredentialStore, PgLockoutStore};
+
+pub const DEFAULT_MIN_LENGTH: usize = 12;
+pub const DEFAULT_MAX_LENGTH: usize = 128;
+pub const UNLOCK_TOKEN_TTL_MINUTES: i64 = 30;
+// RFC 9106's second recommended argon2id setting
+const ARGON2_MEMORY_KIB: u32 = 64 * 1024;
+const ARGON2_ITERATIONS: u32 = 3;
+const ARGON2_LANES: u32 = 4;
+
+static COMMON_PASSWORDS: Lazy<HashSet<&'static str>> = Lazy::new(|| {
+    include_str!("common_passwords.txt")
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect()
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CharClass {
+    Lowercase,
+    Uppercase,
+    Digit,
+    Symbol,
+}
+
+impl CharClass {
+    fn matches(self, c: char) -> bool {
+        match self {
+            CharClass::Lowercase => c.is_lowercase(),
+            CharClass::Uppercase => c.is_uppercase(),
+            CharClass::Digit => c.is_numeric(),
+            CharClass::Symbol => !c.is_alphanumeric() && !c.is_whitespace(),
+        }
+    }
+}
+
+impl fmt::Display for CharClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            CharClass::Lowercase => "lowercase letter",
+            CharClass::Uppercase => "uppercase letter",
+            CharClass::Digit => "digit",
+            CharClass::Symbol => "symbol",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PolicyViolation {
+    TooShort { min: usize },
+    TooLong { max: usize },
+    MissingClass(CharClass),
+    Common,
+    ContainsEmail,
+    Breached { occurrences: u64 },
+}
+
+impl fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PolicyViolation::TooShort { min } => write!(f, "must be at least {} characters", min),
+            PolicyViolation::TooLong { max } => write!(f, "must be at most {} characters", max),
+            PolicyViolation::MissingClass(class) => write!(f, "must contain a {}", class),
+            PolicyViolation::Common => f.write_str("is one of the most commonly used passwords"),
+            PolicyViolation::ContainsEmail => f.write_str("must not contain your email address"),
+            PolicyViolation::Breached { occurrences } => write!(f, "has appeared in {} known data breaches", occurrences),
+        }
+    }
+}
+
+// Length and the deny-list are on by default; character classes are opt-in
+#[derive(Debug, Clone)]
+pub struct PasswordPolicy {
+    min_length: usize,
+    max_length: usize,
+    required_classes: Vec<CharClass>,
+    deny_common: bool,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PasswordPolicy {
+    pub fn new() -> Self {
+        Self {
+            min_length: DEFAULT_MIN_LENGTH,
+            max_length: DEFAULT_MAX_LENGTH,
+            required_classes: Vec::new(),
+            deny_common: true,
+        }
+    }
+
+    pub fn with_min_length(mut self, min_length: usize) -> Self {
+        self.min_length = min_length;
+        self
+    }
+
+    pub fn with_max_length(mut self, max_length: usize) -> Self {
+        self.max_length = max_length;
+        self
+    }
+
+    pub fn with_required_class(mut self, class: CharClass) -> Self {
+        if !self.required_classes.contains(&class) {
+            self.required_classes.push(class);
+        }
+        self
+    }
+
+    pub fn with_common_deny_list(mut self, enabled: bool) -> Self {
+        self.deny_common = enabled;
+        self
+    }
+
+    // Everything wrong with the password, so the user can fix it in one go
+    pub fn check(&self, password: &str, email: &str) -> Vec<PolicyViolation> {
+        let mut violations = Vec::new();
+        let length = password.chars().count();
+        if length < self.min_length {
+            violations.push(PolicyViolation::TooShort { min: self.min_length });
+        }
+        if length > self.max_length {
+            violations.push(PolicyViolation::TooLong { max: self.max_length });
+        }
+        for class in &self.required_classes {
+            if !password.chars().any(|c| class.matches(c)) {
+                violations.push(PolicyViolation::MissingClass(*class));
+            }
+        }
+        let lowered = password.to_lowercase();
+        if self.deny_common && COMMON_PASSWORDS.contains(lowered.as_str()) {
+            violations.push(PolicyViolation::Common);
+        }
+        let local_part = email.split('@').next().unwrap_or_default().to_lowercase();
+        if local_part.chars().count() >= 3 && lowered.contains(&local_part) {
+            violations.push(PolicyViolation::ContainsEmail);
+        }
+        violations
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PasswordCheck {
+    Mismatch,
+    // Correct, but hashed with an older algorithm or cost than the current one
+    Match { needs_rehash: bool },
+}
+
+#[async_trait]
+pub trait CredentialStore: Send + Sync {
+    async fn find_by_email(&self, email: &str) -> AppResult<Option<User>>;
+    async fn find_by_id(&self, id: Uuid) -> AppResult<Option<User>>;
+    async fn create(&self, name: &str, email: &str, password_hash: &str) -> AppResult<User>;
+    async fn update_password_hash(&self, id: Uuid, password_hash: &str) -> AppResult<()>;
+}
+
+// Delivers the unlock link; core has no mailer of its own
+#[async_trait]
+pub trait UnlockNotifier: Send + Sync {
+    async fn send_unlock_link(&self, user: &User, token: &str, expires_at: DateTime<Utc>) -> AppResult<()>;
+}
+
+fn hash_token(raw: &str) -> String {
+    format!("{:x}", Sha256::digest(raw.as_bytes()))
+}
+
+// Email and password sign-in for local accounts
+pub struct LocalAuthService {
+    credentials: Arc<dyn CredentialStore>,
+    throttle: LoginThrottle,
+    policy: PasswordPolicy,
+    params: Params,
+    breach: Option<Arc<dyn BreachChecker>>,
+    notifier: Option<Arc<dyn UnlockNotifier>>,
+    // Verified against for unknown emails so they take as long as wrong passwords
+    dummy_hash: OnceCell<String>,
+}
+
+impl LocalAuthService {
+    pub fn new(credentials: Arc<dyn CredentialStore>, throttle: LoginThrottle) -> Self {
+        Self {
+            credentials,
+            throttle,
+            policy: PasswordPolicy::default(),
+            params: Params::new(ARGON2_MEMORY_KIB, ARGON2_ITERATIONS, ARGON2_LANES, None).expect("valid argon2 parameters"),
+            breach: None,
+            notifier: None,
+            dummy_hash: OnceCell::new(),
+        }
+    }
+
+    pub fn with_policy(mut self, policy: PasswordPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    // Existing hashes with different parameters are upgraded at their next sign-in
+    pub fn with_hash_params(mut self, memory_kib: u32, iterations: u32, lanes: u32) -> AppResult<Self> {
+        self.params = Params::new(memory_kib, iterations, lanes, None)
+            .map_err(|e| AppError::Configuration(format!("Invalid argon2 parameters: {}", e)))?;
+        Ok(self)
+    }
+
+    pub fn with_breach_checker(mut self, checker: Arc<dyn BreachChecker>) -> Self {
+        self.breach = Some(checker);
+        self
+    }
+
+    pub fn with_unlock_notifier(mut self, notifier: Arc<dyn UnlockNotifier>) -> Self {
+        self.notifier = Some(notifier);
+        self
+    }
+
+    fn hasher(&self) -> Argon2<'static> {
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, self.params.clone())
+    }
+
+    pub fn hash_password(&self, password: &str) -> AppResult<String> {
+        let salt = SaltString::generate(&mut OsRng);
+        self.hasher()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(|e| AppError::Internal(e.to_string()))
+    }
+
+    pub fn verify_password(&self, password: &str, stored: &str) -> PasswordCheck {
+        let Ok(parsed) = PasswordHash::new(stored) else {
+            warn!("Stored password hash is not in PHC format");
+            return PasswordCheck::Mismatch;
+        };
+        // The verifier takes algorithm, version and cost from the stored hash
+        if self.hasher().verify_password(password.as_bytes(), &parsed).is_err() {
+            return PasswordCheck::Mismatch;
+        }
+        let current = Params::try_from(&parsed).ok();
+        let needs_rehash = parsed.algorithm != Algorithm::Argon2id.ident()
+            || parsed.version != Some(Version::V0x13.into())
+            || current.is_none_or(|p| {
+                (p.m_cost(), p.t_cost(), p.p_cost()) != (self.params.m_cost(), self.params.t_cost(), self.params.p_cost())
+            });
+        PasswordCheck::Match { needs_rehash }
+    }
+
+    fn dummy_hash(&self) -> &str {
+        self.dummy_hash.get_or_init(|| self.hash_password(&Uuid::new_v4().to_string()).unwrap_or_default())
+    }
+
+    // Local rules first; the breach check is best effort and never blocks on an outage
+    pub async fn validate_password(&self, password: &str, email: &str) -> AppResult<()> {
+        let mut violations = self.policy.check(password, email);
+        if violations.is_empty() {
+            if let Some(breach) = &self.breach {
+                match breach.occurrences(password).await {
+                    Ok(0) => {}
+                    Ok(occurrences) => violations.push(PolicyViolation::Breached { occurrences }),
+                    Err(e) => warn!("Skipping breached password check: {}", e),
+                }
+            }
+        }
+        if violations.is_empty() {
+            return Ok(());
+        }
+        let reasons: Vec<String> = violations.iter().map(ToString::to_string).collect();
+        Err(AppError::Validation(format!("Password {}", reasons.join("; "))))
+    }
+
+    pub async fn register(&self, name: &str, email: &str, password: &str) -> AppResult<User> {
+        self.validate_password(password, email).await?;
+        let hash = self.hash_password(password)?;
+        self.credentials.create(name, email, &hash).await
+    }
+
+    // Unknown emails and wrong passwords fail identically, and both count towards the lockout
+    pub async fn authenticate(&self, email: &str, password: &str, client_ip: Option<&str>, now: DateTime<Utc>) -> AppResult<User> {
+        self.throttle.check(email, client_ip, now).await?;
+        let user = self.credentials.find_by_email(email).await?;
+        let stored = user.as_ref().map_or_else(|| self.dummy_hash(), |u| u.password_hash.as_str());
+        let check = self.verify_password(password, stored);
+
+        let (user, needs_rehash) = match (user, check) {
+            (Some(user), PasswordCheck::Match { needs_rehash }) => (user, needs_rehash),
+            _ => {
+                self.throttle.record_failure(email, client_ip, now).await?;
+                return Err(AppError::Auth("Invalid email or password".into()));
+            }
+        };
+        self.throttle.clear_account(email).await?;
+
+        if needs_rehash {
+            match self.hash_password(password) {
+                Ok(hash) => match self.credentials.update_password_hash(user.id, &hash).await {
+                    Ok(()) => {
+                        info!("Upgraded password hash for {}", user.id);
+                        return Ok(User { password_hash: hash, ..user });
+                    }
+                    Err(e) => warn!("Failed to upgrade password hash for {}: {}", user.id, e),
+                },
+                Err(e) => warn!("Failed to upgrade password hash for {}: {}", user.id, e),
+            }
+        }
+        Ok(user)
+    }
+
+    pub async fn change_password(&self, user_id: Uuid, current: &str, new: &str, now: DateTime<Utc>) -> AppResult<()> {
+        let user = self
+            .credentials
+            .find_by_id(user_id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("User not found".into()))?;
+        self.throttle.check(&user.email, None, now).await?;
+        if self.verify_password(current, &user.password_hash) == PasswordCheck::Mismatch {
+            self.throttle.record_failure(&user.email, None, now).await?;
+            return Err(AppError::Auth("Current password is incorrect".into()));
+        }
+        if current == new {
+            return Err(AppError::Validation("Password must differ from the current one".into()));
+        }
+        self.validate_password(new, &user.email).await?;
+        let hash = self.hash_password(new)?;
+        self.credentials.update_password_hash(user.id, &hash).await?;
+        info!("Password changed for {}", user.id);
+        Ok(())
+    }
+
+    // Says nothing about whether the account exists or is locked; only a locked account
+    // gets a link
+    pub async fn request_unlock(&self, email: &str, now: DateTime<Utc>) -> AppResult<()> {
+        let Some(mut entry) = self.throttle.account_entry(email).await? else {
+            return Ok(());
+        };
+        if !self.throttle.is_blocked(&entry, now) {
+            return Ok(());
+        }
+        let Some(user) = self.credentials.find_by_email(email).await? else {
+            return Ok(());
+        };
+        let Some(notifier) = &self.notifier else {
+            warn!("Unlock requested for {} but no unlock notifier is configured", user.id);
+            return Ok(());
+        };
+
+        let token = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+        let expires_at = now + Duration::minutes(UNLOCK_TOKEN_TTL_MINUTES);
+        entry.unlock_token_hash = Some(hash_token(&token));
+        entry.unlock_expires_at = Some(expires_at);
+        self.throttle.store().put(&entry).await?;
+        notifier.send_unlock_link(&user, &token, expires_at).await
+    }
+
+    pub async fn redeem_unlock(&self, token: &str, now: DateTime<Utc>) -> AppResult<()> {
+        let entry = self
+            .throttle
+            .store()
+            .find_by_unlock_token(&hash_token(token.trim()))
+            .await?
+            .filter(|e| e.unlock_expires_at.is_some_and(|at| at > now))
+            .ok_or_else(|| AppError::Validation("Unlock link is invalid or has expired".into()))?;
+        self.throttle.store().delete(&entry.key).await?;
+        info!("Unlocked {} by email link", entry.key);
+        Ok(())
+    }
+
+    pub async fn admin_unlock(&self, user_id: Uuid) -> AppResult<User> {
+        let user = self
+            .credentials
+            .find_by_id(user_id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("User not found".into()))?;
+        self.throttle.clear_account(&user.email).await?;
+        Ok(user)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::Mutex;
+
+    #[derive(Default)]
+    struct CapturingNotifier {
+        tokens: Mutex<Vec<(String, String)>>,
+    }
+
+    #[async_trait]
+    impl UnlockNotifier for CapturingNotifier {
+        async fn send_unlock_link(&self, user: &User, token: &str, _expires_at: DateTime<Utc>) -> AppResult<()> {
+            self.tokens.lock().await.push((user.email.clone(), token.to_string()));
+            Ok(())
+        }
+    }
+
+    fn service(notifier: Arc<CapturingNotifier>) -> (LocalAuthService, Arc<InMemoryCredentialStore>) {
+        let credentials = Arc::new(InMemoryCredentialStore::new());
+        let throttle = LoginThrottle::new(Arc::new(InMemoryLockoutStore::new()))
+            .with_account_policy(LockoutPolicy::new(2, 4).with_lock_duration(Duration::minutes(10)));
+        let service = LocalAuthService::new(credentials.clone(), throttle)
+            .with_hash_params(1024, 1, 1)
+            .unwrap()
+            .with_unlock_notifier(notifier);
+        (service, credentials)
+    }
+
+    #[test]
+    fn test_policy_lists_every_violation() {
+        let policy = PasswordPolicy::new().with_required_class(CharClass::Digit).with_required_class(CharClass::Symbol);
+        assert_eq!(
+            policy.check("password1", "ada@example.com"),
+            vec![PolicyViolation::TooShort { min: 12 }, PolicyViolation::MissingClass(CharClass::Symbol), PolicyViolation::Common]
+        );
+        assert_eq!(policy.check("ada-lovelace-1815", "ada.lovelace@example.com"), vec![]);
+        assert_eq!(policy.check("xx-Ada.Lovelace-99", "ada.lovelace@example.com"), vec![PolicyViolation::ContainsEmail]);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_and_known_accounts_fail_and_lock_identically() {
+        let notifier = Arc::new(CapturingNotifier::default());
+        let (service, _) = service(notifier.clone());
+        let now = Utc::now();
+        let err = service.register("Ada", "ada@example.com", "password1").await.unwrap_err().to_string();
+        assert!(err.contains("at least 12 characters") && err.contains("most commonly used"), "{}", err);
+        service.register("Ada", "ada@example.com", "analytical-engine-1843").await.unwrap();
+
+        let mut at = now;
+        for attempt in 0..4 {
+            let known = service.authenticate("ada@example.com", "wrong-password", Some("203.0.113.9"), at).await.unwrap_err();
+            let unknown = service.authenticate("nobody@example.com", "wrong-password", Some("198.51.100.7"), at).await.unwrap_err();
+            assert!(matches!(&known, AppError::Auth(_)), "attempt {}: {:?}", attempt, known);
+            assert_eq!(known.to_string(), unknown.to_string());
+            at += Duration::seconds(60);
+        }
+        let known = service.authenticate("ada@example.com", "analytical-engine-1843", None, at).await.unwrap_err();
+        let unknown = service.authenticate("nobody@example.com", "wrong-password", None, at).await.unwrap_err();
+        assert!(matches!(known, AppError::RateLimited { .. }));
+        assert_eq!(format!("{:?}", known), format!("{:?}", unknown));
+
+        // Unlock requests look the same either way; only the real account gets a link
+        service.request_unlock("nobody@example.com", at).await.unwrap();
+        service.request_unlock("ada@example.com", at).await.unwrap();
+        let (email, token) = notifier.tokens.lock().await.pop().unwrap();
+        assert_eq!(email, "ada@example.com");
+        assert!(notifier.tokens.lock().await.is_empty());
+        assert!(service.redeem_unlock(&token, at + Duration::minutes(UNLOCK_TOKEN_TTL_MINUTES)).await.is_err());
+        service.redeem_unlock(&token, at).await.unwrap();
+        assert!(service.redeem_unlock(&token, at).await.is_err());
+        service.authenticate("ada@example.com", "analytical-engine-1843", None, at).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_legacy_hash_is_upgraded_on_login() {
+        let (service, credentials) = service(Arc::new(CapturingNotifier::default()));
+        let legacy = Argon2::new(Algorithm::Argon2i, Version::V0x10, Params::new(512, 2, 1, None).unwrap())
+            .hash_password(b"difference-engine", &SaltString::generate(&mut OsRng))
+            .unwrap()
+            .to_string();
+        let user = credentials.create("Charles", "charles@example.com", &legacy).await.unwrap();
+        assert_eq!(service.verify_password("difference-engine", &legacy), PasswordCheck::Match { needs_rehash: true });
+
+        let signed_in = service.authenticate("charles@example.com", "difference-engine", None, Utc::now()).await.unwrap();
+        let upgraded = credentials.find_by_id(user.id).await.unwrap().unwrap().password_hash;
+        assert!(upgraded.starts_with("$argon2id$v=19$m=1024,t=1,p=1$"), "{}", upgraded);
+        assert_eq!(signed_in.password_hash, upgraded);
+        assert_eq!(service.verify_password("difference-engine", &upgraded), PasswordCheck::Match { needs_rehash: false });
+
+        service.authenticate("charles@example.com", "difference-engine", None, Utc::now()).await.unwrap();
+        assert_eq!(credentials.find_by_id(user.id).await.unwrap().unwrap().password_hash, upgraded);
+    }
+}
@@ -0,0 +1,191 @@
+use std::collections::HashMap;
+
+use axum::async_trait;
+use chrono::Utc;
+use sqlx::PgPool; // CockroachDB uses PostgreSQL protocol
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use super::{CredentialStore, LockoutEntry, LockoutStore};
+use crate::error::{AppError, AppResult};
+use crate::models::user::User;
+
+const LOCKOUT_COLUMNS: &str = "key, failures, last_failure_at, locked_until, unlock_token_hash, unlock_expires_at";
+
+pub struct PgCredentialStore {
+    pool: PgPool,
+}
+
+impl PgCredentialStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl CredentialStore for PgCredentialStore {
+    async fn find_by_email(&self, email: &str) -> AppResult<Option<User>> {
+        User::find_by_email(&self.pool, email).await
+    }
+
+    async fn find_by_id(&self, id: Uuid) -> AppResult<Option<User>> {
+        User::find_by_id(&self.pool, id).await
+    }
+
+    async fn create(&self, name: &str, email: &str, password_hash: &str) -> AppResult<User> {
+        User::create(&self.pool, name, email, password_hash).await
+    }
+
+    async fn update_password_hash(&self, id: Uuid, password_hash: &str) -> AppResult<()> {
+        User::update_password_hash(&self.pool, id, password_hash).await
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryCredentialStore {
+    users: Mutex<HashMap<Uuid, User>>,
+}
+
+impl InMemoryCredentialStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl CredentialStore for InMemoryCredentialStore {
+    async fn find_by_email(&self, email: &str) -> AppResult<Option<User>> {
+        Ok(self.users.lock().await.values().find(|u| u.email == email).cloned())
+    }
+
+    async fn find_by_id(&self, id: Uuid) -> AppResult<Option<User>> {
+        Ok(self.users.lock().await.get(&id).cloned())
+    }
+
+    async fn create(&self, name: &str, email: &str, password_hash: &str) -> AppResult<User> {
+        let mut users = self.users.lock().await;
+        if users.values().any(|u| u.email == email) {
+            return Err(AppError::Conflict { message: "Email already registered".into(), current: None });
+        }
+        let now = Utc::now().naive_utc();
+        let user = User {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            email: email.to_string(),
+            password_hash: password_hash.to_string(),
+            created_at: Some(now),
+            updated_at: Some(now),
+        };
+        users.insert(user.id, user.clone());
+        Ok(user)
+    }
+
+    async fn update_password_hash(&self, id: Uuid, password_hash: &str) -> AppResult<()> {
+        let mut users = self.users.lock().await;
+        let user = users.get_mut(&id).ok_or_else(|| AppError::NotFound("User not found".into()))?;
+        user.password_hash = password_hash.to_string();
+        user.updated_at = Some(Utc::now().naive_utc());
+        Ok(())
+    }
+}
+
+pub struct PgLockoutStore {
+    pool: PgPool,
+}
+
+impl PgLockoutStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl LockoutStore for PgLockoutStore {
+    async fn get(&self, key: &str) -> AppResult<Option<LockoutEntry>> {
+        let sql = format!("SELECT {} FROM login_lockouts WHERE key = $1", LOCKOUT_COLUMNS);
+        let entry = sqlx::query_as::<_, LockoutEntry>(&sql)
+            .bind(key)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(entry)
+    }
+
+    async fn put(&self, entry: &LockoutEntry) -> AppResult<()> {
+        sqlx::query(
+            r#"UPSERT INTO login_lockouts (key, failures, last_failure_at, locked_until, unlock_token_hash, unlock_expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6)"#
+        )
+        .bind(&entry.key)
+        .bind(entry.failures)
+        .bind(entry.last_failure_at)
+        .bind(entry.locked_until)
+        .bind(&entry.unlock_token_hash)
+        .bind(entry.unlock_expires_at)
+        .execute(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> AppResult<()> {
+        sqlx::query("DELETE FROM login_lockouts WHERE key = $1")
+            .bind(key)
+            .execute(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(())
+    }
+
+    async fn find_by_unlock_token(&self, token_hash: &str) -> AppResult<Option<LockoutEntry>> {
+        let sql = format!("SELECT {} FROM login_lockouts WHERE unlock_token_hash = $1", LOCKOUT_COLUMNS);
+        let entry = sqlx::query_as::<_, LockoutEntry>(&sql)
+            .bind(token_hash)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(entry)
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryLockoutStore {
+    entries: Mutex<HashMap<String, LockoutEntry>>,
+}
+
+impl InMemoryLockoutStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl LockoutStore for InMemoryLockoutStore {
+    async fn get(&self, key: &str) -> AppResult<Option<LockoutEntry>> {
+        Ok(self.entries.lock().await.get(key).cloned())
+    }
+
+    async fn put(&self, entry: &LockoutEntry) -> AppResult<()> {
+        self.entries.lock().await.insert(entry.key.clone(), entry.clone());
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> AppResult<()> {
+        self.entries.lock().await.remove(key);
+        Ok(())
+    }
+
+    async fn find_by_unlock_token(&self, token_hash: &str) -> AppResult<Option<LockoutEntry>> {
+        Ok(self
+            .entries
+            .lock()
+            .await
+            .values()
+            .find(|e| e.unlock_token_hash.as_deref() == Some(token_hash))
+            .cloned())
+    }
+}
@@ -0,0 +1,719 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration as StdDuration;
+
+use axum::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::api::export::ExportStorage;
+use crate::error::{AppError, AppResult};
+
+pub mod store;
+
+pub use store::{pg_subject_tables, InMemoryPrivacyStore, PgPrivacyAudit, PgPrivacyStore, PgSubjectDirectory, PgSubjectTable};
+
+const DEFAULT_LINK_TTL: StdDuration = StdDuration::from_secs(15 * 60);
+// A request left running this long by a dead worker is picked up again; both jobs are idempotent
+const DEFAULT_LEASE_MINUTES: i64 = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "VARCHAR", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum PrivacyRequestKind {
+    Export,
+    Erasure,
+}
+
+impl PrivacyRequestKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PrivacyRequestKind::Export => "export",
+            PrivacyRequestKind::Erasure => "erasure",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "VARCHAR", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum PrivacyRequestStatus {
+    PendingApproval,
+    Approved,
+    Running,
+    Completed,
+    Rejected,
+    // The subject still owns data that has to be transferred; a new request is needed afterwards
+    Blocked,
+    Failed,
+}
+
+impl PrivacyRequestStatus {
+    pub fn is_open(&self) -> bool {
+        matches!(self, PrivacyRequestStatus::PendingApproval | PrivacyRequestStatus::Approved | PrivacyRequestStatus::Running)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct PrivacyRequest {
+    pub id: Uuid,
+    pub kind: PrivacyRequestKind,
+    pub subject_id: Uuid,
+    pub requested_by: Uuid,
+    pub reason: Option<String>,
+    pub status: PrivacyRequestStatus,
+    pub reviewed_by: Option<Uuid>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+    pub review_note: Option<String>,
+    #[serde(skip_serializing)]
+    pub object_key: Option<String>,
+    // Row counts for an export; the erasure verification report for an erasure
+    pub report: Option<Value>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PrivacyRequestResponse {
+    #[serde(flatten)]
+    pub request: PrivacyRequest,
+    // Presigned link to a completed export, minted fresh on every poll
+    pub download_url: Option<String>,
+}
+
+// The personal identifiers erasure looks for, captured before anything is changed
+#[derive(Debug, Clone, Serialize)]
+pub struct Subject {
+    pub id: Uuid,
+    pub email: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ErasureBlocker {
+    pub kind: String,
+    pub id: Uuid,
+    pub name: String,
+    // What has to happen before erasure can go ahead
+    pub action: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ErasureAction {
+    // Personal fields are overwritten; the row stays so references to it remain valid
+    Anonymize,
+    Delete,
+    // Exported but left untouched by erasure
+    Retain,
+}
+
+// How erasure treats each table; tables without an entry are retained
+#[derive(Debug, Clone, Serialize)]
+pub struct ErasurePolicy {
+    tables: BTreeMap<String, ErasureAction>,
+}
+
+impl Default for ErasurePolicy {
+    fn default() -> Self {
+        Self::new()
+            .with_table("users", ErasureAction::Anonymize)
+            .with_table("organizations", ErasureAction::Anonymize)
+            .with_table("organization_members", ErasureAction::Delete)
+            .with_table("organization_invitations", ErasureAction::Delete)
+            .with_table("api_keys", ErasureAction::Delete)
+            .with_table("audit_events", ErasureAction::Anonymize)
+            .with_table("impersonation_sessions", ErasureAction::Anonymize)
+            .with_table("device_authorizations", ErasureAction::Delete)
+            .with_table("login_lockouts", ErasureAction::Delete)
+            .with_table("export_jobs", ErasureAction::Delete)
+            .with_table("agent_sessions", ErasureAction::Delete)
+    }
+}
+
+impl ErasurePolicy {
+    pub fn new() -> Self {
+        Self { tables: BTreeMap::new() }
+    }
+
+    pub fn with_table(mut self, table: &str, action: ErasureAction) -> Self {
+        self.tables.insert(table.to_string(), action);
+        self
+    }
+
+    pub fn action(&self, table: &str) -> ErasureAction {
+        self.tables.get(table).copied().unwrap_or(ErasureAction::Retain)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TableReport {
+    pub table: String,
+    pub action: ErasureAction,
+    pub affected: u64,
+    // Linked rows still holding personal data once the action ran
+    pub remaining: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErasureReport {
+    pub tables: Vec<TableReport>,
+    pub verified: bool,
+}
+
+#[async_trait]
+pub trait PrivacyRequestStore: Send + Sync {
+    async fn create(&self, request: &PrivacyRequest) -> AppResult<()>;
+    async fn get(&self, id: Uuid) -> AppResult<Option<PrivacyRequest>>;
+    async fn list(&self, status: Option<PrivacyRequestStatus>, limit: i64) -> AppResult<Vec<PrivacyRequest>>;
+    async fn open_for(&self, subject_id: Uuid, kind: PrivacyRequestKind) -> AppResult<Option<PrivacyRequest>>;
+    async fn update(&self, request: &PrivacyRequest) -> AppResult<()>;
+    // Moves the oldest approved request, or one whose worker died, to running
+    async fn claim(&self, now: DateTime<Utc>, stale_before: DateTime<Utc>) -> AppResult<Option<PrivacyRequest>>;
+}
+
+#[async_trait]
+pub trait SubjectDirectory: Send + Sync {
+    async fn find(&self, subject_id: Uuid) -> AppResult<Option<Subject>>;
+    async fn blockers(&self, subject_id: Uuid) -> AppResult<Vec<ErasureBlocker>>;
+}
+
+// One table (or store) holding data linked to a user
+#[async_trait]
+pub trait SubjectTable: Send + Sync {
+    fn name(&self) -> &'static str;
+    async fn collect(&self, subject: &Subject) -> AppResult<Vec<Value>>;
+    async fn anonymize(&self, subject: &Subject) -> AppResult<u64> {
+        Err(AppError::Configuration(format!("{} can't be anonymized for {}", self.name(), subject.id)))
+    }
+    async fn delete(&self, subject: &Subject) -> AppResult<u64> {
+        Err(AppError::Configuration(format!("{} can't be deleted for {}", self.name(), subject.id)))
+    }
+    // Linked rows that still hold the subject's personal data
+    async fn remaining(&self, subject: &Subject) -> AppResult<u64>;
+}
+
+#[async_trait]
+pub trait PrivacyAudit: Send + Sync {
+    async fn record(&self, request: &PrivacyRequest, action: &str, details: Value);
+}
+
+pub struct PrivacyService {
+    requests: Arc<dyn PrivacyRequestStore>,
+    directory: Arc<dyn SubjectDirectory>,
+    tables: Vec<Arc<dyn SubjectTable>>,
+    policy: ErasurePolicy,
+    // Exports can't run until storage is attached
+    storage: Option<Arc<dyn ExportStorage>>,
+    audit: Option<Arc<dyn PrivacyAudit>>,
+    link_ttl: StdDuration,
+}
+
+impl PrivacyService {
+    pub fn new(requests: Arc<dyn PrivacyRequestStore>, directory: Arc<dyn SubjectDirectory>) -> Self {
+        Self {
+            requests,
+            directory,
+            tables: Vec::new(),
+            policy: ErasurePolicy::default(),
+            storage: None,
+            audit: None,
+            link_ttl: DEFAULT_LINK_TTL,
+        }
+    }
+
+    // Tables are erased in the order they are added
+    pub fn with_table(mut self, table: Arc<dyn SubjectTable>) -> Self {
+        self.tables.retain(|t| t.name() != table.name());
+        self.tables.push(table);
+        self
+    }
+
+    pub fn with_tables(self, tables: Vec<Arc<dyn SubjectTable>>) -> Self {
+        tables.into_iter().fold(self, Self::with_table)
+    }
+
+    pub fn with_policy(mut self, policy: ErasurePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn with_storage(mut self, storage: Arc<dyn ExportStorage>) -> Self {
+        self.storage = Some(storage);
+        self
+    }
+
+    pub fn with_audit(mut self, audit: Arc<dyn PrivacyAudit>) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    pub fn with_link_ttl(mut self, ttl: StdDuration) -> Self {
+        self.link_ttl = ttl;
+        self
+    }
+
+    pub async fn request(
+        &self,
+        kind: PrivacyRequestKind,
+        subject_id: Uuid,
+        requested_by: Uuid,
+        reason: Option<String>,
+        now: DateTime<Utc>,
+    ) -> AppResult<PrivacyRequest> {
+        if self.directory.find(subject_id).await?.is_none() {
+            return Err(AppError::NotFound("User not found".into()));
+        }
+        if kind == PrivacyRequestKind::Export && self.storage.is_none() {
+            return Err(AppError::Configuration("No export storage is configured".into()));
+        }
+        if let Some(open) = self.requests.open_for(subject_id, kind).await? {
+            return Err(AppError::Conflict {
+                message: format!("An {} request for this user is already open", kind.as_str()),
+                current: serde_json::to_value(&open).ok(),
+            });
+        }
+        if kind == PrivacyRequestKind::Erasure {
+            self.ensure_unblocked(subject_id).await?;
+        }
+
+        let request = PrivacyRequest {
+            id: Uuid::new_v4(),
+            kind,
+            subject_id,
+            requested_by,
+            reason,
+            status: PrivacyRequestStatus::PendingApproval,
+            reviewed_by: None,
+            reviewed_at: None,
+            review_note: None,
+            object_key: None,
+            report: None,
+            error: None,
+            created_at: now,
+            started_at: None,
+            completed_at: None,
+        };
+        self.requests.create(&request).await?;
+        info!("{} request {} for {} awaits approval", kind.as_str(), request.id, subject_id);
+        Ok(request)
+    }
+
+    // A second person has to sign off, and never the subject themselves
+    pub async fn approve(&self, id: Uuid, reviewer: Uuid, note: Option<String>, now: DateTime<Utc>) -> AppResult<PrivacyRequest> {
+        let mut request = self.pending(id).await?;
+        if reviewer == request.requested_by || reviewer == request.subject_id {
+            return Err(AppError::Forbidden("A privacy request must be approved by someone other than the requester or subject".into()));
+        }
+        if request.kind == PrivacyRequestKind::Erasure {
+            self.ensure_unblocked(request.subject_id).await?;
+        }
+        request.status = PrivacyRequestStatus::Approved;
+        request.reviewed_by = Some(reviewer);
+        request.reviewed_at = Some(now);
+        request.review_note = note;
+        self.requests.update(&request).await?;
+        Ok(request)
+    }
+
+    pub async fn reject(&self, id: Uuid, reviewer: Uuid, note: Option<String>, now: DateTime<Utc>) -> AppResult<PrivacyRequest> {
+        let mut request = self.pending(id).await?;
+        request.status = PrivacyRequestStatus::Rejected;
+        request.reviewed_by = Some(reviewer);
+        request.reviewed_at = Some(now);
+        request.review_note = note;
+        request.completed_at = Some(now);
+        self.requests.update(&request).await?;
+        Ok(request)
+    }
+
+    async fn pending(&self, id: Uuid) -> AppResult<PrivacyRequest> {
+        let request = self.requests.get(id).await?.ok_or_else(|| AppError::NotFound("Privacy request not found".into()))?;
+        if request.status != PrivacyRequestStatus::PendingApproval {
+            return Err(AppError::Conflict {
+                message: "Privacy request has already been reviewed".into(),
+                current: serde_json::to_value(&request).ok(),
+            });
+        }
+        Ok(request)
+    }
+
+    async fn ensure_unblocked(&self, subject_id: Uuid) -> AppResult<()> {
+        let blockers = self.directory.blockers(subject_id).await?;
+        if blockers.is_empty() {
+            return Ok(());
+        }
+        Err(AppError::Conflict { message: blocked_message(&blockers), current: Some(json!({ "blockers": blockers })) })
+    }
+
+    pub async fn get(&self, id: Uuid) -> AppResult<PrivacyRequestResponse> {
+        let request = self.requests.get(id).await?.ok_or_else(|| AppError::NotFound("Privacy request not found".into()))?;
+        let download_url = match (&self.storage, request.status, &request.object_key) {
+            (Some(storage), PrivacyRequestStatus::Completed, Some(key)) => Some(storage.presigned_url(key, self.link_ttl).await?),
+            _ => None,
+        };
+        Ok(PrivacyRequestResponse { request, download_url })
+    }
+
+    pub async fn list(&self, status: Option<PrivacyRequestStatus>, limit: i64) -> AppResult<Vec<PrivacyRequest>> {
+        self.requests.list(status, limit).await
+    }
+
+    // Runs at most one approved request
+    pub async fn process_once(&self, now: DateTime<Utc>) -> AppResult<Option<PrivacyRequest>> {
+        let stale_before = now - Duration::minutes(DEFAULT_LEASE_MINUTES);
+        let Some(mut request) = self.requests.claim(now, stale_before).await? else {
+            return Ok(None);
+        };
+
+        let result = match request.kind {
+            PrivacyRequestKind::Export => self.run_export(&mut request).await,
+            PrivacyRequestKind::Erasure => self.run_erasure(&mut request).await,
+        };
+        match result {
+            Ok(status) => request.status = status,
+            Err(e) => {
+                error!("{} request {} failed: {}", request.kind.as_str(), request.id, e);
+                request.status = PrivacyRequestStatus::Failed;
+                request.error = Some(e.to_string());
+            }
+        }
+        request.completed_at = Some(Utc::now());
+        self.requests.update(&request).await?;
+        if let Some(audit) = &self.audit {
+            let action = format!("privacy.{}_{}", request.kind.as_str(), status_name(request.status));
+            audit.record(&request, &action, json!({ "request_id": request.id, "report": request.report, "error": request.error })).await;
+        }
+        Ok(Some(request))
+    }
+
+    async fn run_export(&self, request: &mut PrivacyRequest) -> AppResult<PrivacyRequestStatus> {
+        let storage = self.storage.as_ref().ok_or_else(|| AppError::Configuration("No export storage is configured".into()))?;
+        let subject = self.subject(request.subject_id).await?;
+
+        let mut sections = serde_json::Map::new();
+        let mut counts = serde_json::Map::new();
+        for table in &self.tables {
+            let rows = table.collect(&subject).await?;
+            counts.insert(table.name().to_string(), json!(rows.len()));
+            sections.insert(table.name().to_string(), Value::Array(rows));
+        }
+        let archive = json!({
+            "subject_id": subject.id,
+            "request_id": request.id,
+            "generated_at": Utc::now(),
+            "data": sections,
+        });
+        let body = serde_json::to_vec_pretty(&archive).map_err(|e| AppError::Serialization(e.to_string()))?;
+
+        let path = std::env::temp_dir().join(format!("sirsi-privacy-export-{}.json", request.id));
+        tokio::fs::write(&path, body)
+            .await
+            .map_err(|e| AppError::Internal(format!("Failed to write privacy export: {}", e)))?;
+        let key = format!("privacy/{}/{}.json", subject.id, request.id);
+        let uploaded = storage.upload(&key, &path, "application/json").await;
+        if let Err(e) = tokio::fs::remove_file(&path).await {
+            warn!("Failed to remove privacy export file {}: {}", path.display(), e);
+        }
+        uploaded?;
+
+        request.object_key = Some(key);
+        request.report = Some(json!({ "rows": counts }));
+        Ok(PrivacyRequestStatus::Completed)
+    }
+
+    async fn run_erasure(&self, request: &mut PrivacyRequest) -> AppResult<PrivacyRequestStatus> {
+        // Ownership could have been picked up since approval
+        let blockers = self.directory.blockers(request.subject_id).await?;
+        if !blockers.is_empty() {
+            request.error = Some(blocked_message(&blockers));
+            request.report = Some(json!({ "blockers": blockers }));
+            return Ok(PrivacyRequestStatus::Blocked);
+        }
+        let subject = self.subject(request.subject_id).await?;
+
+        let mut tables = Vec::new();
+        for table in &self.tables {
+            let action = self.policy.action(table.name());
+            let affected = match action {
+                ErasureAction::Anonymize => table.anonymize(&subject).await?,
+                ErasureAction::Delete => table.delete(&subject).await?,
+                ErasureAction::Retain => 0,
+            };
+            tables.push(TableReport { table: table.name().to_string(), action, affected, remaining: 0 });
+        }
+        // Verified only after every table has been processed, so one table can't re-expose another
+        for (report, table) in tables.iter_mut().zip(&self.tables) {
+            if report.action != ErasureAction::Retain {
+                report.remaining = table.remaining(&subject).await?;
+            }
+        }
+        let verified = tables.iter().all(|t| t.remaining == 0);
+        let report = ErasureReport { tables, verified };
+        request.report = serde_json::to_value(&report).ok();
+        if !verified {
+            request.error = Some("Personal data remains after erasure; see the report".into());
+            return Ok(PrivacyRequestStatus::Failed);
+        }
+        info!("Erased personal data of {} for request {}", subject.id, request.id);
+        Ok(PrivacyRequestStatus::Completed)
+    }
+
+    async fn subject(&self, subject_id: Uuid) -> AppResult<Subject> {
+        self.directory.find(subject_id).await?.ok_or_else(|| AppError::NotFound("User not found".into()))
+    }
+
+    pub fn spawn_worker(self: Arc<Self>, interval: StdDuration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                loop {
+                    match self.process_once(Utc::now()).await {
+                        Ok(Some(_)) => continue,
+                        Ok(None) => break,
+                        Err(e) => {
+                            warn!("Privacy request processing failed: {}", e);
+                            break;
+                        }
+                    }
+                }
+            }
+        })
+    }
+}
+
+fn status_name(status: PrivacyRequestStatus) -> &'static str {
+    match status {
+        PrivacyRequestStatus::PendingApproval => "pending_approval",
+        PrivacyRequestStatus::Approved => "approved",
+        PrivacyRequestStatus::Running => "running",
+        PrivacyRequestStatus::Completed => "completed",
+        PrivacyRequestStatus::Rejected => "rejected",
+        PrivacyRequestStatus::Blocked => "blocked",
+        PrivacyRequestStatus::Failed => "failed",
+    }
+}
+
+fn blocked_message(blockers: &[ErasureBlocker]) -> String {
+    let steps: Vec<String> = blockers.iter().map(|b| format!("{} {} ({}): {}", b.kind, b.name, b.id, b.action)).collect();
+    format!("The user still owns data that must be transferred before erasure: {}", steps.join("; "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::export::InMemoryExportStorage;
+    use std::sync::Mutex;
+
+    #[derive(Debug, Clone, Serialize)]
+    struct User {
+        id: Uuid,
+        email: String,
+        name: String,
+    }
+
+    #[derive(Debug, Clone, Serialize)]
+    struct Project {
+        id: Uuid,
+        owner_id: Uuid,
+    }
+
+    #[derive(Debug, Clone, Serialize)]
+    struct AuditRow {
+        owner_id: Uuid,
+        actor_id: Uuid,
+        details: Value,
+    }
+
+    #[derive(Default, Serialize)]
+    struct Db {
+        users: Vec<User>,
+        projects: Vec<Project>,
+        members: Vec<(Uuid, Uuid)>,
+        audit: Vec<AuditRow>,
+    }
+
+    #[derive(Clone, Default)]
+    struct Fixture(Arc<Mutex<Db>>);
+
+    #[async_trait]
+    impl SubjectDirectory for Fixture {
+        async fn find(&self, subject_id: Uuid) -> AppResult<Option<Subject>> {
+            let db = self.0.lock().unwrap();
+            Ok(db.users.iter().find(|u| u.id == subject_id).map(|u| Subject { id: u.id, email: u.email.clone(), name: u.name.clone() }))
+        }
+
+        async fn blockers(&self, subject_id: Uuid) -> AppResult<Vec<ErasureBlocker>> {
+            let db = self.0.lock().unwrap();
+            Ok(db
+                .projects
+                .iter()
+                .filter(|p| p.owner_id == subject_id)
+                .map(|p| ErasureBlocker { kind: "project".into(), id: p.id, name: "p".into(), action: "transfer".into() })
+                .collect())
+        }
+    }
+
+    struct Users(Fixture);
+    struct Members(Fixture);
+    struct Audit(Fixture);
+
+    #[async_trait]
+    impl SubjectTable for Users {
+        fn name(&self) -> &'static str {
+            "users"
+        }
+        async fn collect(&self, subject: &Subject) -> AppResult<Vec<Value>> {
+            let db = self.0 .0.lock().unwrap();
+            Ok(db.users.iter().filter(|u| u.id == subject.id).map(|u| json!(u)).collect())
+        }
+        async fn anonymize(&self, subject: &Subject) -> AppResult<u64> {
+            let mut db = self.0 .0.lock().unwrap();
+            let user = db.users.iter_mut().find(|u| u.id == subject.id).unwrap();
+            user.email = format!("erased-{}@erased.invalid", user.id);
+            user.name.clear();
+            Ok(1)
+        }
+        async fn remaining(&self, subject: &Subject) -> AppResult<u64> {
+            let db = self.0 .0.lock().unwrap();
+            Ok(db.users.iter().filter(|u| u.id == subject.id && (u.email == subject.email || !u.name.is_empty())).count() as u64)
+        }
+    }
+
+    #[async_trait]
+    impl SubjectTable for Members {
+        fn name(&self) -> &'static str {
+            "organization_members"
+        }
+        async fn collect(&self, subject: &Subject) -> AppResult<Vec<Value>> {
+            let db = self.0 .0.lock().unwrap();
+            Ok(db.members.iter().filter(|(_, u)| *u == subject.id).map(|(org, _)| json!({ "org_id": org })).collect())
+        }
+        async fn delete(&self, subject: &Subject) -> AppResult<u64> {
+            let mut db = self.0 .0.lock().unwrap();
+            let before = db.members.len();
+            db.members.retain(|(_, u)| *u != subject.id);
+            Ok((before - db.members.len()) as u64)
+        }
+        async fn remaining(&self, subject: &Subject) -> AppResult<u64> {
+            Ok(self.0 .0.lock().unwrap().members.iter().filter(|(_, u)| *u == subject.id).count() as u64)
+        }
+    }
+
+    #[async_trait]
+    impl SubjectTable for Audit {
+        fn name(&self) -> &'static str {
+            "audit_events"
+        }
+        async fn collect(&self, subject: &Subject) -> AppResult<Vec<Value>> {
+            let db = self.0 .0.lock().unwrap();
+            Ok(db.audit.iter().filter(|a| a.actor_id == subject.id).map(|a| json!(a)).collect())
+        }
+        async fn anonymize(&self, subject: &Subject) -> AppResult<u64> {
+            let mut db = self.0 .0.lock().unwrap();
+            let mut affected = 0;
+            for row in db.audit.iter_mut().filter(|a| a.actor_id == subject.id || a.details.to_string().contains(&subject.email)) {
+                row.details = json!({ "erased": true });
+                affected += 1;
+            }
+            Ok(affected)
+        }
+        async fn remaining(&self, subject: &Subject) -> AppResult<u64> {
+            let db = self.0 .0.lock().unwrap();
+            Ok(db.audit.iter().filter(|a| a.details.to_string().contains(&subject.email)).count() as u64)
+        }
+    }
+
+    fn seeded() -> (Fixture, User, User) {
+        let fixture = Fixture::default();
+        let subject = User { id: Uuid::new_v4(), email: "ada@example.com".into(), name: "Ada Lovelace".into() };
+        let other = User { id: Uuid::new_v4(), email: "grace@example.com".into(), name: "Grace".into() };
+        {
+            let mut db = fixture.0.lock().unwrap();
+            db.users = vec![subject.clone(), other.clone()];
+            db.projects.push(Project { id: Uuid::new_v4(), owner_id: other.id });
+            db.members = vec![(Uuid::new_v4(), subject.id), (Uuid::new_v4(), other.id)];
+            db.audit.push(AuditRow { owner_id: subject.id, actor_id: subject.id, details: json!({ "email": subject.email }) });
+            db.audit.push(AuditRow { owner_id: other.id, actor_id: other.id, details: json!({ "invited": subject.email, "by": other.name }) });
+            db.audit.push(AuditRow { owner_id: other.id, actor_id: other.id, details: json!({ "name": "unrelated" }) });
+        }
+        (fixture, subject, other)
+    }
+
+    fn service(fixture: &Fixture) -> PrivacyService {
+        PrivacyService::new(Arc::new(InMemoryPrivacyStore::new()), Arc::new(fixture.clone()))
+            .with_table(Arc::new(Members(fixture.clone())))
+            .with_table(Arc::new(Audit(fixture.clone())))
+            .with_table(Arc::new(Users(fixture.clone())))
+    }
+
+    #[tokio::test]
+    async fn test_erasure_leaves_no_personal_data_and_keeps_references_valid() {
+        let (fixture, subject, other) = seeded();
+        let admin = Uuid::new_v4();
+        let service = service(&fixture);
+
+        // Blocked while the subject still owns a project
+        let owned = Project { id: Uuid::new_v4(), owner_id: subject.id };
+        fixture.0.lock().unwrap().projects.push(owned.clone());
+        let blocked = service.request(PrivacyRequestKind::Erasure, subject.id, subject.id, None, Utc::now()).await;
+        assert!(matches!(blocked, Err(AppError::Conflict { current: Some(ref c), .. }) if c["blockers"][0]["id"] == json!(owned.id)));
+        fixture.0.lock().unwrap().projects.retain(|p| p.id != owned.id);
+
+        let request = service.request(PrivacyRequestKind::Erasure, subject.id, subject.id, None, Utc::now()).await.unwrap();
+        // Nothing runs before approval, and the subject can't approve their own request
+        assert!(service.process_once(Utc::now()).await.unwrap().is_none());
+        assert!(matches!(service.approve(request.id, subject.id, None, Utc::now()).await, Err(AppError::Forbidden(_))));
+        service.approve(request.id, admin, None, Utc::now()).await.unwrap();
+
+        let done = service.process_once(Utc::now()).await.unwrap().unwrap();
+        assert_eq!(done.status, PrivacyRequestStatus::Completed, "{:?}", done.error);
+        let report: ErasureReport = serde_json::from_value(done.report.unwrap()).unwrap();
+        assert!(report.verified);
+        assert_eq!(report.tables.iter().find(|t| t.table == "audit_events").unwrap().affected, 2);
+
+        let db = fixture.0.lock().unwrap();
+        let everything = serde_json::to_string(&*db).unwrap();
+        assert!(!everything.contains(&subject.email) && !everything.contains(&subject.name));
+        assert!(everything.contains(&other.email) && everything.contains("unrelated"));
+        // Every reference to a user still resolves
+        let user_ids: Vec<Uuid> = db.users.iter().map(|u| u.id).collect();
+        assert!(db.audit.iter().all(|a| user_ids.contains(&a.owner_id) && user_ids.contains(&a.actor_id)));
+        assert!(db.projects.iter().all(|p| user_ids.contains(&p.owner_id)));
+        assert!(db.members.iter().all(|(_, u)| user_ids.contains(u)));
+    }
+
+    #[tokio::test]
+    async fn test_export_collects_every_table_into_a_downloadable_archive() {
+        let (fixture, subject, _) = seeded();
+        let storage = Arc::new(InMemoryExportStorage::new());
+        let service = service(&fixture).with_storage(storage.clone());
+        let admin = Uuid::new_v4();
+
+        let request = service.request(PrivacyRequestKind::Export, subject.id, subject.id, None, Utc::now()).await.unwrap();
+        assert!(matches!(
+            service.request(PrivacyRequestKind::Export, subject.id, subject.id, None, Utc::now()).await,
+            Err(AppError::Conflict { .. })
+        ));
+        service.approve(request.id, admin, None, Utc::now()).await.unwrap();
+        service.process_once(Utc::now()).await.unwrap().unwrap();
+
+        let response = service.get(request.id).await.unwrap();
+        assert_eq!(response.request.status, PrivacyRequestStatus::Completed);
+        assert!(response.download_url.is_some());
+        let key = response.request.object_key.unwrap();
+        let archive: Value = serde_json::from_slice(&storage.object(&key).await.unwrap()).unwrap();
+        assert_eq!(archive["data"]["users"][0]["email"], json!(subject.email));
+        assert_eq!(archive["data"]["organization_members"].as_array().unwrap().len(), 1);
+        assert_eq!(archive["data"]["audit_events"].as_array().unwrap().len(), 1);
+        // Exporting doesn't change anything
+        assert!(serde_json::to_string(&*fixture.0.lock().unwrap()).unwrap().contains(&subject.email));
+    }
+}
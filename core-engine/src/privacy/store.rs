@@ -0,0 +1,463 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use sqlx::PgPool; // CockroachDB uses PostgreSQL protocol
+use tokio::sync::Mutex;
+use tracing::warn;
+use uuid::Uuid;
+
+use super::{
+    ErasureBlocker, PrivacyAudit, PrivacyRequest, PrivacyRequestKind, PrivacyRequestStatus, PrivacyRequestStore, Subject,
+    SubjectDirectory, SubjectTable,
+};
+use crate::error::{AppError, AppResult};
+use crate::models::AuditEvent;
+
+const REQUEST_COLUMNS: &str = "id, kind, subject_id, requested_by, reason, status, reviewed_by, reviewed_at, review_note, \
+    object_key, report, error, created_at, started_at, completed_at";
+
+pub struct PgPrivacyStore {
+    pool: PgPool,
+}
+
+impl PgPrivacyStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl PrivacyRequestStore for PgPrivacyStore {
+    async fn create(&self, request: &PrivacyRequest) -> AppResult<()> {
+        sqlx::query(
+            r#"INSERT INTO privacy_requests (id, kind, subject_id, requested_by, reason, status, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)"#
+        )
+        .bind(request.id)
+        .bind(request.kind)
+        .bind(request.subject_id)
+        .bind(request.requested_by)
+        .bind(&request.reason)
+        .bind(request.status)
+        .bind(request.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(ref db) if db.is_unique_violation() => {
+                AppError::Conflict { message: format!("An {} request for this user is already open", request.kind.as_str()), current: None }
+            }
+            e => AppError::Database(e),
+        })?;
+
+        Ok(())
+    }
+
+    async fn get(&self, id: Uuid) -> AppResult<Option<PrivacyRequest>> {
+        let sql = format!("SELECT {} FROM privacy_requests WHERE id = $1", REQUEST_COLUMNS);
+        let request = sqlx::query_as::<_, PrivacyRequest>(&sql)
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(request)
+    }
+
+    async fn list(&self, status: Option<PrivacyRequestStatus>, limit: i64) -> AppResult<Vec<PrivacyRequest>> {
+        let sql = format!(
+            "SELECT {} FROM privacy_requests WHERE ($1::STRING IS NULL OR status = $1) ORDER BY created_at DESC LIMIT $2",
+            REQUEST_COLUMNS
+        );
+        let requests = sqlx::query_as::<_, PrivacyRequest>(&sql)
+            .bind(status)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(requests)
+    }
+
+    async fn open_for(&self, subject_id: Uuid, kind: PrivacyRequestKind) -> AppResult<Option<PrivacyRequest>> {
+        let sql = format!(
+            "SELECT {} FROM privacy_requests WHERE subject_id = $1 AND kind = $2 AND status IN ('pending_approval', 'approved', 'running')",
+            REQUEST_COLUMNS
+        );
+        let request = sqlx::query_as::<_, PrivacyRequest>(&sql)
+            .bind(subject_id)
+            .bind(kind)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(request)
+    }
+
+    async fn update(&self, request: &PrivacyRequest) -> AppResult<()> {
+        sqlx::query(
+            r#"UPDATE privacy_requests
+            SET status = $2, reviewed_by = $3, reviewed_at = $4, review_note = $5, object_key = $6, report = $7,
+                error = $8, started_at = $9, completed_at = $10
+            WHERE id = $1"#
+        )
+        .bind(request.id)
+        .bind(request.status)
+        .bind(request.reviewed_by)
+        .bind(request.reviewed_at)
+        .bind(&request.review_note)
+        .bind(&request.object_key)
+        .bind(&request.report)
+        .bind(&request.error)
+        .bind(request.started_at)
+        .bind(request.completed_at)
+        .execute(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(())
+    }
+
+    async fn claim(&self, now: DateTime<Utc>, stale_before: DateTime<Utc>) -> AppResult<Option<PrivacyRequest>> {
+        let sql = format!(
+            r#"UPDATE privacy_requests SET status = 'running', started_at = $1
+            WHERE id = (
+                SELECT id FROM privacy_requests
+                WHERE status = 'approved' OR (status = 'running' AND started_at < $2)
+                ORDER BY reviewed_at LIMIT 1
+            )
+            RETURNING {}"#,
+            REQUEST_COLUMNS
+        );
+        let request = sqlx::query_as::<_, PrivacyRequest>(&sql)
+            .bind(now)
+            .bind(stale_before)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(request)
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryPrivacyStore {
+    requests: Mutex<HashMap<Uuid, PrivacyRequest>>,
+}
+
+impl InMemoryPrivacyStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl PrivacyRequestStore for InMemoryPrivacyStore {
+    async fn create(&self, request: &PrivacyRequest) -> AppResult<()> {
+        self.requests.lock().await.insert(request.id, request.clone());
+        Ok(())
+    }
+
+    async fn get(&self, id: Uuid) -> AppResult<Option<PrivacyRequest>> {
+        Ok(self.requests.lock().await.get(&id).cloned())
+    }
+
+    async fn list(&self, status: Option<PrivacyRequestStatus>, limit: i64) -> AppResult<Vec<PrivacyRequest>> {
+        let requests = self.requests.lock().await;
+        let mut matching: Vec<PrivacyRequest> = requests.values().filter(|r| status.is_none_or(|s| r.status == s)).cloned().collect();
+        matching.sort_by_key(|r| std::cmp::Reverse(r.created_at));
+        matching.truncate(limit.max(0) as usize);
+        Ok(matching)
+    }
+
+    async fn open_for(&self, subject_id: Uuid, kind: PrivacyRequestKind) -> AppResult<Option<PrivacyRequest>> {
+        let requests = self.requests.lock().await;
+        Ok(requests.values().find(|r| r.subject_id == subject_id && r.kind == kind && r.status.is_open()).cloned())
+    }
+
+    async fn update(&self, request: &PrivacyRequest) -> AppResult<()> {
+        self.requests.lock().await.insert(request.id, request.clone());
+        Ok(())
+    }
+
+    async fn claim(&self, now: DateTime<Utc>, stale_before: DateTime<Utc>) -> AppResult<Option<PrivacyRequest>> {
+        let mut requests = self.requests.lock().await;
+        let next = requests
+            .values_mut()
+            .filter(|r| {
+                r.status == PrivacyRequestStatus::Approved
+                    || (r.status == PrivacyRequestStatus::Running && r.started_at.is_some_and(|s| s < stale_before))
+            })
+            .min_by_key(|r| r.reviewed_at);
+        Ok(next.map(|request| {
+            request.status = PrivacyRequestStatus::Running;
+            request.started_at = Some(now);
+            request.clone()
+        }))
+    }
+}
+
+pub struct PgSubjectDirectory {
+    pool: PgPool,
+}
+
+impl PgSubjectDirectory {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl SubjectDirectory for PgSubjectDirectory {
+    async fn find(&self, subject_id: Uuid) -> AppResult<Option<Subject>> {
+        let row: Option<(Uuid, String, String)> = sqlx::query_as("SELECT id, email, name FROM users WHERE id = $1")
+            .bind(subject_id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(row.map(|(id, email, name)| Subject { id, email, name }))
+    }
+
+    // Owned projects and resources, and organizations only the subject administers
+    async fn blockers(&self, subject_id: Uuid) -> AppResult<Vec<ErasureBlocker>> {
+        let rows: Vec<(String, Uuid, String)> = sqlx::query_as(
+            r#"SELECT 'project', id, name FROM projects WHERE owner_id = $1
+            UNION ALL
+            SELECT 'resource', id, name FROM resources WHERE owner_id = $1
+            UNION ALL
+            SELECT 'organization', o.id, o.name FROM organizations o
+            JOIN organization_members m ON m.org_id = o.id AND m.user_id = $1 AND m.role = 'admin'
+            WHERE EXISTS (SELECT 1 FROM organization_members x WHERE x.org_id = o.id AND x.user_id != $1)
+            AND NOT EXISTS (SELECT 1 FROM organization_members x WHERE x.org_id = o.id AND x.user_id != $1 AND x.role = 'admin')
+            ORDER BY 1, 3"#
+        )
+        .bind(subject_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(kind, id, name)| {
+                let action = match kind.as_str() {
+                    "project" => format!("Transfer it with POST /projects/{}/transfer", id),
+                    "resource" => "Delete it or recreate it under another owner".to_string(),
+                    _ => format!("Make another member an admin with PUT /orgs/{}/members/:user_id", id),
+                };
+                ErasureBlocker { kind, id, name, action }
+            })
+            .collect())
+    }
+}
+
+// A table described by its SQL. Every statement may use $1 (the subject's id), $2 (their email)
+// and $3 (their name); a value is only bound when its placeholder appears
+pub struct PgSubjectTable {
+    pool: PgPool,
+    name: &'static str,
+    collect: &'static str,
+    anonymize: Option<&'static str>,
+    delete: Option<&'static str>,
+    remaining: &'static str,
+}
+
+impl PgSubjectTable {
+    pub fn new(pool: PgPool, name: &'static str, collect: &'static str, remaining: &'static str) -> Self {
+        Self { pool, name, collect, anonymize: None, delete: None, remaining }
+    }
+
+    pub fn with_anonymize(mut self, sql: &'static str) -> Self {
+        self.anonymize = Some(sql);
+        self
+    }
+
+    pub fn with_delete(mut self, sql: &'static str) -> Self {
+        self.delete = Some(sql);
+        self
+    }
+
+    fn bind<'q, O>(
+        sql: &str,
+        mut query: sqlx::query::QueryAs<'q, sqlx::Postgres, O, sqlx::postgres::PgArguments>,
+        subject: &'q Subject,
+    ) -> sqlx::query::QueryAs<'q, sqlx::Postgres, O, sqlx::postgres::PgArguments> {
+        query = query.bind(subject.id);
+        if sql.contains("$2") {
+            query = query.bind(&subject.email);
+        }
+        if sql.contains("$3") {
+            query = query.bind(&subject.name);
+        }
+        query
+    }
+
+    async fn execute(&self, sql: &str, subject: &Subject) -> AppResult<u64> {
+        let mut query = sqlx::query(sql).bind(subject.id);
+        if sql.contains("$2") {
+            query = query.bind(&subject.email);
+        }
+        if sql.contains("$3") {
+            query = query.bind(&subject.name);
+        }
+        let result = query.execute(&self.pool).await.map_err(AppError::Database)?;
+        Ok(result.rows_affected())
+    }
+}
+
+#[async_trait]
+impl SubjectTable for PgSubjectTable {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    async fn collect(&self, subject: &Subject) -> AppResult<Vec<Value>> {
+        let rows: Vec<(Value,)> = Self::bind(self.collect, sqlx::query_as(self.collect), subject)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(rows.into_iter().map(|(row,)| row).collect())
+    }
+
+    async fn anonymize(&self, subject: &Subject) -> AppResult<u64> {
+        match self.anonymize {
+            Some(sql) => self.execute(sql, subject).await,
+            None => Err(AppError::Configuration(format!("{} can't be anonymized", self.name))),
+        }
+    }
+
+    async fn delete(&self, subject: &Subject) -> AppResult<u64> {
+        match self.delete {
+            Some(sql) => self.execute(sql, subject).await,
+            None => Err(AppError::Configuration(format!("{} can't be deleted", self.name))),
+        }
+    }
+
+    async fn remaining(&self, subject: &Subject) -> AppResult<u64> {
+        let (count,): (i64,) = Self::bind(self.remaining, sqlx::query_as(self.remaining), subject)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(count as u64)
+    }
+}
+
+// Every table in this database that holds data linked to a user, in erasure order. Users come
+// last so the rows pointing at them are dealt with first
+pub fn pg_subject_tables(pool: &PgPool) -> Vec<Arc<dyn SubjectTable>> {
+    let tables = vec![
+        PgSubjectTable::new(
+            pool.clone(),
+            "organization_members",
+            r#"SELECT row_to_json(t)::JSONB FROM (
+                SELECT m.org_id, o.name AS org_name, m.role, m.created_at,
+                    ARRAY(SELECT p.id::STRING FROM projects p WHERE p.org_id = m.org_id) AS project_ids
+                FROM organization_members m JOIN organizations o ON o.id = m.org_id WHERE m.user_id = $1) t"#,
+            "SELECT COUNT(*) FROM organization_members WHERE user_id = $1",
+        )
+        .with_delete("DELETE FROM organization_members WHERE user_id = $1"),
+        PgSubjectTable::new(
+            pool.clone(),
+            "organizations",
+            "SELECT row_to_json(t)::JSONB FROM (SELECT id, name, created_at FROM organizations WHERE personal_for = $1) t",
+            "SELECT COUNT(*) FROM organizations WHERE personal_for = $1 AND name IN ($2, $3)",
+        )
+        .with_anonymize("UPDATE organizations SET name = 'Personal' WHERE personal_for = $1 AND name IN ($2, $3)"),
+        PgSubjectTable::new(
+            pool.clone(),
+            "organization_invitations",
+            "SELECT row_to_json(t)::JSONB FROM (SELECT id, org_id, role, expires_at, accepted_at, created_at FROM organization_invitations WHERE email = $2) t",
+            "SELECT COUNT(*) FROM organization_invitations WHERE email = $2",
+        )
+        .with_delete("DELETE FROM organization_invitations WHERE email = $2"),
+        PgSubjectTable::new(
+            pool.clone(),
+            "api_keys",
+            r#"SELECT row_to_json(t)::JSONB FROM (
+                SELECT id, name, prefix, scopes, expires_at, last_used_at, revoked_at, created_at FROM api_keys WHERE owner_id = $1) t"#,
+            "SELECT COUNT(*) FROM api_keys WHERE owner_id = $1",
+        )
+        .with_delete("DELETE FROM api_keys WHERE owner_id = $1"),
+        // Entries stay as evidence that something happened; only their details can name the subject
+        PgSubjectTable::new(
+            pool.clone(),
+            "audit_events",
+            r#"SELECT row_to_json(t)::JSONB FROM (
+                SELECT id, owner_id, action, target_type, target_id, details, created_at FROM audit_events WHERE actor_id = $1
+                ORDER BY created_at) t"#,
+            "SELECT COUNT(*) FROM audit_events WHERE strpos(details::STRING, $2) > 0",
+        )
+        .with_anonymize(
+            r#"UPDATE audit_events SET details = '{"erased": true}'
+            WHERE actor_id = $1 OR owner_id = $1 OR target_id = $1 OR strpos(details::STRING, $2) > 0"#,
+        ),
+        PgSubjectTable::new(
+            pool.clone(),
+            "impersonation_sessions",
+            r#"SELECT row_to_json(t)::JSONB FROM (
+                SELECT id, actor_id, target_id, reason, started_at, expires_at, revoked_at FROM impersonation_sessions
+                WHERE actor_id = $1 OR target_id = $1) t"#,
+            "SELECT COUNT(*) FROM impersonation_sessions WHERE (actor_id = $1 OR target_id = $1) AND reason != '[erased]'",
+        )
+        .with_anonymize("UPDATE impersonation_sessions SET reason = '[erased]' WHERE actor_id = $1 OR target_id = $1"),
+        PgSubjectTable::new(
+            pool.clone(),
+            "device_authorizations",
+            "SELECT row_to_json(t)::JSONB FROM (SELECT id, client_id, status, created_at FROM device_authorizations WHERE user_id = $1) t",
+            "SELECT COUNT(*) FROM device_authorizations WHERE user_id = $1",
+        )
+        .with_delete("DELETE FROM device_authorizations WHERE user_id = $1"),
+        PgSubjectTable::new(
+            pool.clone(),
+            "login_lockouts",
+            "SELECT row_to_json(t)::JSONB FROM (SELECT failures, last_failure_at, locked_until FROM login_lockouts WHERE key = 'account:' || lower($2)) t",
+            "SELECT COUNT(*) FROM login_lockouts WHERE key = 'account:' || lower($2)",
+        )
+        .with_delete("DELETE FROM login_lockouts WHERE key = 'account:' || lower($2)"),
+        PgSubjectTable::new(
+            pool.clone(),
+            "export_jobs",
+            "SELECT row_to_json(t)::JSONB FROM (SELECT id, kind, format, status, created_at, completed_at FROM export_jobs WHERE owner_id = $1) t",
+            "SELECT COUNT(*) FROM export_jobs WHERE owner_id = $1",
+        )
+        .with_delete("DELETE FROM export_jobs WHERE owner_id = $1"),
+        // Referenced from projects, resources and audit events, so the row is kept with nothing personal left
+        PgSubjectTable::new(
+            pool.clone(),
+            "users",
+            "SELECT row_to_json(t)::JSONB FROM (SELECT id, email, name, created_at, updated_at FROM users WHERE id = $1) t",
+            "SELECT COUNT(*) FROM users WHERE id = $1 AND (email = $2 OR name != '' OR password_hash != '!')",
+        )
+        .with_anonymize(
+            r#"UPDATE users SET email = 'erased-' || id::STRING || '@erased.invalid', name = '', password_hash = '!',
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = $1"#,
+        ),
+    ];
+    tables.into_iter().map(|t| Arc::new(t) as Arc<dyn SubjectTable>).collect()
+}
+
+// Completion lands in the approving admin's log, since the subject's own log is anonymized
+pub struct PgPrivacyAudit {
+    pool: PgPool,
+}
+
+impl PgPrivacyAudit {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl PrivacyAudit for PgPrivacyAudit {
+    async fn record(&self, request: &PrivacyRequest, action: &str, details: Value) {
+        let actor = request.reviewed_by.unwrap_or(request.requested_by);
+        if let Err(e) = AuditEvent::record(&self.pool, actor, actor, action, "privacy", Some(request.id), details).await {
+            warn!("Failed to record audit event {} for {}: {}", action, request.id, e);
+        }
+    }
+}
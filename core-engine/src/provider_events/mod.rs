@@ -0,0 +1,666 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tokio::sync::broadcast;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::error::{AppError, AppResult};
+use crate::inventory::{InventoryResource, InventoryService};
+use crate::webhooks::{NewEvent, WebhookService};
+
+pub mod sns;
+pub mod store;
+
+pub use sns::{HttpSnsTransport, SnsMessage, SnsTransport, SnsVerifier, MESSAGE_TYPE_HEADER};
+pub use store::{InMemoryProviderEventStore, PgProviderEventStore};
+
+// EventBridge API destinations send it through an API-key connection
+pub const TOKEN_HEADER: &str = "X-Sirsi-Event-Token";
+const TOKEN_PREFIX: &str = "evsrc_";
+const CHANGE_BUFFER: usize = 256;
+
+// Where one tenant's AWS events arrive: directly from an EventBridge API destination holding the
+// token, or through SNS topics listed here
+#[derive(Debug, Clone, Serialize)]
+pub struct EventSource {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub description: Option<String>,
+    #[serde(skip_serializing)]
+    pub token_hash: String,
+    pub topic_arns: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewEventSource {
+    pub description: Option<String>,
+    #[serde(default)]
+    pub topic_arns: Vec<String>,
+}
+
+// The EventBridge envelope shared by every AWS service's events
+#[derive(Debug, Clone, Deserialize)]
+pub struct EventBridgeEvent {
+    pub id: String,
+    #[serde(rename = "detail-type")]
+    pub detail_type: String,
+    pub source: String,
+    pub account: String,
+    pub time: DateTime<Utc>,
+    pub region: String,
+    #[serde(default)]
+    pub resources: Vec<String>,
+    #[serde(default)]
+    pub detail: Value,
+}
+
+impl EventBridgeEvent {
+    // How unmapped events are counted
+    pub fn event_type(&self) -> String {
+        format!("{}:{}", self.source, self.detail_type)
+    }
+
+    fn detail_str(&self, field: &str) -> Option<&str> {
+        self.detail.get(field).and_then(Value::as_str)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResourceChange {
+    // `state` replaces the resource's state when set; attributes are merged over its own
+    Updated { state: Option<String>, attributes: BTreeMap<String, Value> },
+    Removed,
+}
+
+// A provider event about one resource, normalized across event sources
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ResourceEvent {
+    pub tenant_id: Uuid,
+    // The provider's event id; redeliveries repeat it
+    pub event_id: String,
+    pub event_type: String,
+    pub provider: String,
+    pub account_id: String,
+    pub region: String,
+    pub resource_type: String,
+    // Preferred first; the rest are how discovery may have recorded the same resource
+    pub resource_keys: Vec<String>,
+    pub change: ResourceChange,
+    pub occurred_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AppliedEvent {
+    pub event: ResourceEvent,
+    pub before: Option<InventoryResource>,
+    pub after: Option<InventoryResource>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum EventOutcome {
+    Applied(Box<AppliedEvent>),
+    // Already processed under the same event id
+    Duplicate,
+    // The inventory already reflects a later event or discovery run
+    Stale,
+    // Stored raw until a mapping exists
+    Unmapped { event_type: String },
+    SubscriptionConfirmed,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UnmappedEvent {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub source_id: Uuid,
+    pub event_id: String,
+    pub event_type: String,
+    pub payload: Value,
+    pub received_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UnmappedType {
+    pub event_type: String,
+    pub count: i64,
+    pub first_seen_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+}
+
+#[async_trait]
+pub trait ProviderEventStore: Send + Sync {
+    async fn create_source(&self, source: &EventSource) -> AppResult<()>;
+    async fn source(&self, id: Uuid) -> AppResult<Option<EventSource>>;
+    async fn sources(&self, tenant_id: Uuid) -> AppResult<Vec<EventSource>>;
+    async fn delete_source(&self, tenant_id: Uuid, id: Uuid) -> AppResult<bool>;
+    // False when the event id was already claimed
+    async fn claim(&self, tenant_id: Uuid, event_id: &str, now: DateTime<Utc>) -> AppResult<bool>;
+    // Lets a redelivery retry an event whose processing failed
+    async fn release(&self, tenant_id: Uuid, event_id: &str) -> AppResult<()>;
+    // Stores the event and bumps its type's counter
+    async fn record_unmapped(&self, event: &UnmappedEvent) -> AppResult<()>;
+    async fn unmapped_types(&self, tenant_id: Uuid) -> AppResult<Vec<UnmappedType>>;
+    async fn unmapped_events(&self, tenant_id: Uuid, event_type: &str, limit: i64) -> AppResult<Vec<UnmappedEvent>>;
+}
+
+fn hash_token(raw: &str) -> String {
+    format!("{:x}", Sha256::digest(raw.as_bytes()))
+}
+
+fn instance_keys(event: &EventBridgeEvent, instance_id: &str) -> Vec<String> {
+    vec![
+        format!("arn:aws:ec2:{}:{}:instance/{}", event.region, event.account, instance_id),
+        // As the AWS connector records instances
+        format!("arn:aws:ec2:{}::instance/{}", event.region, instance_id),
+        format!("ec2:instance/{}", instance_id),
+    ]
+}
+
+fn instance_update(
+    event: &EventBridgeEvent,
+    instance_id: &str,
+    state: &str,
+    extra: Option<(&str, &str)>,
+) -> (String, Vec<String>, ResourceChange) {
+    let mut attributes = BTreeMap::from([
+        ("resource_id".to_string(), Value::from(instance_id)),
+        ("state".to_string(), Value::from(state)),
+    ]);
+    if let Some((key, value)) = extra {
+        attributes.insert(key.to_string(), value.into());
+    }
+    let change = ResourceChange::Updated { state: Some(state.to_string()), attributes };
+    ("ec2:instance".to_string(), instance_keys(event, instance_id), change)
+}
+
+// RDS reports instance lifecycle as numbered events
+fn rds_state(event_id: &str) -> Option<Option<&'static str>> {
+    match event_id {
+        "RDS-EVENT-0003" => Some(None),
+        "RDS-EVENT-0005" | "RDS-EVENT-0006" | "RDS-EVENT-0088" => Some(Some("available")),
+        "RDS-EVENT-0087" => Some(Some("stopped")),
+        _ => None,
+    }
+}
+
+// None for events with no mapping yet
+pub fn normalize(tenant_id: Uuid, event: &EventBridgeEvent) -> Option<ResourceEvent> {
+    let (resource_type, resource_keys, change) = match (event.source.as_str(), event.detail_type.as_str()) {
+        ("aws.ec2", "EC2 Instance State-change Notification") => {
+            let instance_id = event.detail_str("instance-id")?;
+            instance_update(event, instance_id, event.detail_str("state")?, None)
+        }
+        ("aws.autoscaling", detail_type @ ("EC2 Instance Launch Successful" | "EC2 Instance Terminate Successful")) => {
+            let instance_id = event.detail_str("EC2InstanceId")?;
+            let group = event.detail_str("AutoScalingGroupName").map(|g| ("auto_scaling_group", g));
+            let state = if detail_type.contains("Launch") { "running" } else { "terminated" };
+            instance_update(event, instance_id, state, group)
+        }
+        ("aws.rds", "RDS DB Instance Event") => {
+            let state = rds_state(event.detail_str("EventID")?)?;
+            let identifier = event.detail_str("SourceIdentifier")?;
+            let arn = event
+                .detail_str("SourceArn")
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("arn:aws:rds:{}:{}:db:{}", event.region, event.account, identifier));
+            let change = match state {
+                Some(state) => ResourceChange::Updated {
+                    state: Some(state.to_string()),
+                    attributes: BTreeMap::from([
+                        ("resource_id".to_string(), identifier.into()),
+                        ("state".to_string(), state.into()),
+                    ]),
+                },
+                None => ResourceChange::Removed,
+            };
+            ("rds:db-instance".to_string(), vec![arn, format!("rds:db-instance/{}", identifier)], change)
+        }
+        _ => return None,
+    };
+    Some(ResourceEvent {
+        tenant_id,
+        event_id: event.id.clone(),
+        event_type: event.event_type(),
+        provider: "aws".to_string(),
+        account_id: event.account.clone(),
+        region: event.region.clone(),
+        resource_type,
+        resource_keys,
+        change,
+        occurred_at: event.time,
+    })
+}
+
+pub struct ProviderEventService {
+    store: Arc<dyn ProviderEventStore>,
+    inventory: Arc<InventoryService>,
+    sns: SnsVerifier,
+    webhooks: Option<Arc<WebhookService>>,
+    changes: broadcast::Sender<AppliedEvent>,
+}
+
+impl ProviderEventService {
+    pub fn new(store: Arc<dyn ProviderEventStore>, inventory: Arc<InventoryService>) -> Self {
+        let (changes, _) = broadcast::channel(CHANGE_BUFFER);
+        Self {
+            store,
+            inventory,
+            sns: SnsVerifier::new(Arc::new(HttpSnsTransport::default())),
+            webhooks: None,
+            changes,
+        }
+    }
+
+    pub fn with_sns_transport(mut self, transport: Arc<dyn SnsTransport>) -> Self {
+        self.sns = SnsVerifier::new(transport);
+        self
+    }
+
+    // Inventory changes are also queued as `inventory.resource_*` webhook events
+    pub fn with_webhooks(mut self, webhooks: Arc<WebhookService>) -> Self {
+        self.webhooks = Some(webhooks);
+        self
+    }
+
+    // Inventory changes applied on this replica, for live views
+    pub fn subscribe(&self) -> broadcast::Receiver<AppliedEvent> {
+        self.changes.subscribe()
+    }
+
+    // The token is returned here and never again
+    pub async fn create_source(&self, tenant_id: Uuid, source: NewEventSource, now: DateTime<Utc>) -> AppResult<(EventSource, String)> {
+        if source.topic_arns.iter().any(|arn| !arn.starts_with("arn:aws:sns:") && !arn.starts_with("arn:aws-cn:sns:")) {
+            return Err(AppError::Validation("topic_arns must be SNS topic ARNs".into()));
+        }
+        let token = format!("{}{}{}", TOKEN_PREFIX, Uuid::new_v4().simple(), Uuid::new_v4().simple());
+        let source = EventSource {
+            id: Uuid::new_v4(),
+            tenant_id,
+            description: source.description,
+            token_hash: hash_token(&token),
+            topic_arns: source.topic_arns,
+            created_at: now,
+        };
+        self.store.create_source(&source).await?;
+        Ok((source, token))
+    }
+
+    pub async fn sources(&self, tenant_id: Uuid) -> AppResult<Vec<EventSource>> {
+        self.store.sources(tenant_id).await
+    }
+
+    pub async fn delete_source(&self, tenant_id: Uuid, id: Uuid) -> AppResult<()> {
+        if !self.store.delete_source(tenant_id, id).await? {
+            return Err(AppError::NotFound("Event source not found".into()));
+        }
+        Ok(())
+    }
+
+    pub async fn unmapped_types(&self, tenant_id: Uuid) -> AppResult<Vec<UnmappedType>> {
+        self.store.unmapped_types(tenant_id).await
+    }
+
+    pub async fn unmapped_events(&self, tenant_id: Uuid, event_type: &str, limit: i64) -> AppResult<Vec<UnmappedEvent>> {
+        self.store.unmapped_events(tenant_id, event_type, limit).await
+    }
+
+    async fn event_source(&self, source_id: Uuid) -> AppResult<EventSource> {
+        self.store
+            .source(source_id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Event source not found".into()))
+    }
+
+    // One event from an EventBridge API destination
+    pub async fn receive_direct(&self, source_id: Uuid, token: Option<&str>, body: &[u8], now: DateTime<Utc>) -> AppResult<EventOutcome> {
+        let source = self.event_source(source_id).await?;
+        if token.map(hash_token).as_deref() != Some(source.token_hash.as_str()) {
+            return Err(AppError::Auth("Invalid event source token".into()));
+        }
+        let raw: Value = serde_json::from_slice(body).map_err(|e| AppError::Validation(format!("Invalid event: {}", e)))?;
+        self.apply(&source, raw, now).await
+    }
+
+    // An SNS delivery: a subscription confirmation or a notification wrapping one event
+    pub async fn receive_sns(&self, source_id: Uuid, body: &[u8], now: DateTime<Utc>) -> AppResult<EventOutcome> {
+        let source = self.event_source(source_id).await?;
+        let message: SnsMessage =
+            serde_json::from_slice(body).map_err(|e| AppError::Validation(format!("Invalid SNS message: {}", e)))?;
+        if !source.topic_arns.contains(&message.topic_arn) {
+            return Err(AppError::Forbidden(format!("Topic {} is not registered for this event source", message.topic_arn)));
+        }
+        self.sns.verify(&message).await?;
+        if message.is_subscription_confirmation() {
+            self.sns.confirm(&message).await?;
+            info!("Confirmed SNS subscription of event source {} to {}", source.id, message.topic_arn);
+            return Ok(EventOutcome::SubscriptionConfirmed);
+        }
+        if !message.is_notification() {
+            return Err(AppError::Validation(format!("Unexpected SNS message type '{}'", message.message_type)));
+        }
+        let raw: Value = serde_json::from_str(&message.message)
+            .map_err(|e| AppError::Validation(format!("SNS message is not an EventBridge event: {}", e)))?;
+        self.apply(&source, raw, now).await
+    }
+
+    async fn apply(&self, source: &EventSource, raw: Value, now: DateTime<Utc>) -> AppResult<EventOutcome> {
+        let event: EventBridgeEvent =
+            serde_json::from_value(raw.clone()).map_err(|e| AppError::Validation(format!("Invalid EventBridge event: {}", e)))?;
+        if !self.store.claim(source.tenant_id, &event.id, now).await? {
+            return Ok(EventOutcome::Duplicate);
+        }
+        let outcome = match normalize(source.tenant_id, &event) {
+            Some(resource_event) => self.apply_resource_event(resource_event).await,
+            None => {
+                let unmapped = UnmappedEvent {
+                    id: Uuid::new_v4(),
+                    tenant_id: source.tenant_id,
+                    source_id: source.id,
+                    event_id: event.id.clone(),
+                    event_type: event.event_type(),
+                    payload: raw,
+                    received_at: now,
+                };
+                self.store.record_unmapped(&unmapped).await.map(|_| EventOutcome::Unmapped { event_type: unmapped.event_type })
+            }
+        };
+        if outcome.is_err() {
+            self.store.release(source.tenant_id, &event.id).await?;
+        }
+        outcome
+    }
+
+    async fn current(&self, event: &ResourceEvent) -> AppResult<Option<InventoryResource>> {
+        for key in &event.resource_keys {
+            let found = self.inventory.resource(event.tenant_id, &event.provider, &event.account_id, key, &event.region).await?;
+            if found.is_some() {
+                return Ok(found);
+            }
+        }
+        Ok(None)
+    }
+
+    async fn apply_resource_event(&self, event: ResourceEvent) -> AppResult<EventOutcome> {
+        let before = self.current(&event).await?;
+        if before.as_ref().is_some_and(|r| r.last_seen_at > event.occurred_at) {
+            return Ok(EventOutcome::Stale);
+        }
+        let after = match (&event.change, &before) {
+            (ResourceChange::Removed, Some(existing)) => {
+                self.inventory.remove_resource(existing).await?;
+                None
+            }
+            (ResourceChange::Removed, None) => None,
+            (ResourceChange::Updated { state, attributes }, _) => {
+                let mut resource = before.clone().unwrap_or_else(|| InventoryResource {
+                    id: Uuid::new_v4(),
+                    tenant_id: event.tenant_id,
+                    provider: event.provider.clone(),
+                    account_id: event.account_id.clone(),
+                    resource_key: event.resource_keys[0].clone(),
+                    region: event.region.clone(),
+                    resource_type: event.resource_type.clone(),
+                    name: None,
+                    state: None,
+                    tags: BTreeMap::new(),
+                    attributes: BTreeMap::new(),
+                    first_seen_at: event.occurred_at,
+                    last_seen_at: event.occurred_at,
+                });
+                if state.is_some() {
+                    resource.state = state.clone();
+                }
+                resource.attributes.extend(attributes.clone());
+                resource.last_seen_at = event.occurred_at;
+                self.inventory.put_resource(&resource).await?;
+                Some(resource)
+            }
+        };
+        let applied = AppliedEvent { event, before, after };
+        self.publish(&applied).await;
+        Ok(EventOutcome::Applied(Box::new(applied)))
+    }
+
+    async fn publish(&self, applied: &AppliedEvent) {
+        let (event_type, resource) = match (&applied.before, &applied.after) {
+            (_, Some(after)) => ("inventory.resource_changed", after),
+            (Some(before), None) => ("inventory.resource_removed", before),
+            (None, None) => return,
+        };
+        if let Some(webhooks) = &self.webhooks {
+            let mut event = NewEvent::new(applied.event.tenant_id, event_type, "inventory_resource", resource.id);
+            if let Some(before) = &applied.before {
+                event = event.with_before(json!(before));
+            }
+            if let Some(after) = &applied.after {
+                event = event.with_after(json!(after));
+            }
+            if let Err(e) = webhooks.store().append(&event).await {
+                warn!("Failed to queue {} for resource {}: {}", event_type, resource.id, e);
+            }
+        }
+        // No subscribers is fine
+        let _ = self.changes.send(applied.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discovery::{DiscoveredResource, DiscoveryRun};
+    use crate::inventory::InMemoryInventoryStore;
+    use crate::webhooks::InMemoryWebhookStore;
+    use base64::Engine;
+    use chrono::{Duration, TimeZone};
+    use openssl::asn1::Asn1Time;
+    use openssl::hash::MessageDigest;
+    use openssl::pkey::{PKey, Private};
+    use openssl::rsa::Rsa;
+    use openssl::sign::Signer;
+    use openssl::x509::{X509NameBuilder, X509};
+    use std::sync::Mutex;
+
+    const ACCOUNT: &str = "123456789012";
+    const TOPIC: &str = "arn:aws:sns:us-east-1:123456789012:sirsi-events";
+    const CERT_URL: &str = "https://sns.us-east-1.amazonaws.com/SimpleNotificationService-test.pem";
+
+    struct Fixture {
+        tenant: Uuid,
+        inventory: Arc<InventoryService>,
+        webhooks: Arc<WebhookService>,
+        service: ProviderEventService,
+        discovered_at: DateTime<Utc>,
+    }
+
+    async fn setup() -> Fixture {
+        let tenant = Uuid::new_v4();
+        let discovered_at = Utc.with_ymd_and_hms(2025, 7, 21, 6, 0, 0).unwrap();
+        let inventory = Arc::new(InventoryService::new(Arc::new(InMemoryInventoryStore::new())));
+        let resources = vec![
+            DiscoveredResource::new("ec2:instance", "i-0web", Some("arn:aws:ec2:us-east-1::instance/i-0web"), "us-east-1")
+                .with_name(Some("web".into()))
+                .with_attribute("state", "running")
+                .with_attribute("instance_type", "t3.large"),
+            DiscoveredResource::new("rds:db-instance", "orders", Some("arn:aws:rds:us-east-1:123456789012:db:orders"), "us-east-1"),
+        ];
+        let run = DiscoveryRun {
+            id: Uuid::new_v4(),
+            tenant_id: tenant,
+            provider: "aws".into(),
+            account_id: ACCOUNT.into(),
+            resource_count: resources.len() as i64,
+            started_at: discovered_at,
+            completed_at: discovered_at,
+        };
+        inventory.ingest(&run, &resources).await.unwrap();
+        let webhooks = Arc::new(WebhookService::new(Arc::new(InMemoryWebhookStore::new())));
+        let service = ProviderEventService::new(Arc::new(InMemoryProviderEventStore::new()), inventory.clone())
+            .with_webhooks(webhooks.clone());
+        Fixture { tenant, inventory, webhooks, service, discovered_at }
+    }
+
+    fn eventbridge(id: &str, source: &str, detail_type: &str, at: DateTime<Utc>, detail: Value) -> Value {
+        json!({
+            "version": "0",
+            "id": id,
+            "detail-type": detail_type,
+            "source": source,
+            "account": ACCOUNT,
+            "time": at,
+            "region": "us-east-1",
+            "resources": [],
+            "detail": detail,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_replay_applies_each_event_once() {
+        let fixture = setup().await;
+        let mut changes = fixture.service.subscribe();
+        let (source, token) = fixture.service.create_source(fixture.tenant, NewEventSource { description: None, topic_arns: vec![] }, fixture.discovered_at).await.unwrap();
+        let at = |minutes| fixture.discovered_at + Duration::minutes(minutes);
+        let stopped = eventbridge("ev-1", "aws.ec2", "EC2 Instance State-change Notification", at(5), json!({"instance-id": "i-0web", "state": "stopped"}));
+        let replay = [
+            stopped.clone(),
+            // AWS redelivers
+            stopped,
+            eventbridge("ev-2", "aws.autoscaling", "EC2 Instance Launch Successful", at(6), json!({"AutoScalingGroupName": "web-asg", "EC2InstanceId": "i-0new"})),
+            eventbridge("ev-3", "aws.rds", "RDS DB Instance Event", at(7), json!({"SourceIdentifier": "orders", "SourceArn": "arn:aws:rds:us-east-1:123456789012:db:orders", "EventID": "RDS-EVENT-0003"})),
+            eventbridge("ev-4", "aws.ec2", "EC2 Instance State-change Notification", at(1), json!({"instance-id": "i-0web", "state": "pending"})),
+            eventbridge("ev-5", "aws.s3", "Object Created", at(8), json!({"bucket": {"name": "assets"}})),
+            eventbridge("ev-6", "aws.s3", "Object Created", at(9), json!({"bucket": {"name": "assets"}})),
+        ];
+        let mut outcomes = Vec::new();
+        for (i, event) in replay.iter().enumerate() {
+            let body = serde_json::to_vec(event).unwrap();
+            outcomes.push(fixture.service.receive_direct(source.id, Some(&token), &body, at(10 + i as i64)).await.unwrap());
+        }
+        let body = serde_json::to_vec(&replay[0]).unwrap();
+        assert!(matches!(fixture.service.receive_direct(source.id, Some("evsrc_wrong"), &body, at(20)).await, Err(AppError::Auth(_))));
+
+        let kinds: Vec<&str> = outcomes
+            .iter()
+            .map(|o| match o {
+                EventOutcome::Applied(_) => "applied",
+                EventOutcome::Duplicate => "duplicate",
+                EventOutcome::Stale => "stale",
+                EventOutcome::Unmapped { .. } => "unmapped",
+                EventOutcome::SubscriptionConfirmed => "confirmed",
+            })
+            .collect();
+        assert_eq!(kinds, vec!["applied", "duplicate", "applied", "applied", "stale", "unmapped", "unmapped"]);
+
+        // The discovered instance keeps its identity; the launched one is new
+        let EventOutcome::Applied(stop) = &outcomes[0] else { unreachable!() };
+        let (before, after) = (stop.before.clone().unwrap(), stop.after.clone().unwrap());
+        assert_eq!((before.state.as_deref(), after.state.as_deref()), (Some("running"), Some("stopped")));
+        assert_eq!((after.id, &after.resource_key), (before.id, &"arn:aws:ec2:us-east-1::instance/i-0web".to_string()));
+        assert_eq!((after.attributes["instance_type"].clone(), after.last_seen_at), (json!("t3.large"), at(5)));
+        let launched = fixture
+            .inventory
+            .resource(fixture.tenant, "aws", ACCOUNT, "arn:aws:ec2:us-east-1:123456789012:instance/i-0new", "us-east-1")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!((launched.state.as_deref(), launched.attributes["auto_scaling_group"].clone()), (Some("running"), json!("web-asg")));
+        let orders = fixture.inventory.resource(fixture.tenant, "aws", ACCOUNT, "arn:aws:rds:us-east-1:123456789012:db:orders", "us-east-1");
+        assert!(orders.await.unwrap().is_none());
+
+        let queued: Vec<String> = fixture.webhooks.store().undispatched(100).await.unwrap().into_iter().map(|e| e.event_type).collect();
+        assert_eq!(queued, vec!["inventory.resource_changed", "inventory.resource_changed", "inventory.resource_removed"]);
+        assert_eq!(changes.try_recv().unwrap(), **stop);
+        let unmapped = fixture.service.unmapped_types(fixture.tenant).await.unwrap();
+        assert_eq!((unmapped.len(), unmapped[0].event_type.as_str(), unmapped[0].count), (1, "aws.s3:Object Created", 2));
+        let raw = fixture.service.unmapped_events(fixture.tenant, "aws.s3:Object Created", 10).await.unwrap();
+        assert_eq!(raw[0].payload, replay[6]);
+    }
+
+    #[derive(Default)]
+    struct FakeSns {
+        pem: Vec<u8>,
+        confirmed: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl SnsTransport for FakeSns {
+        async fn signing_cert(&self, url: &str) -> AppResult<Vec<u8>> {
+            assert_eq!(url, CERT_URL);
+            Ok(self.pem.clone())
+        }
+
+        async fn confirm_subscription(&self, subscribe_url: &str) -> AppResult<()> {
+            self.confirmed.lock().unwrap().push(subscribe_url.to_string());
+            Ok(())
+        }
+    }
+
+    fn signing_key() -> (PKey<Private>, Vec<u8>) {
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_text("CN", "sns.amazonaws.com").unwrap();
+        let name = name.build();
+        let mut cert = X509::builder().unwrap();
+        cert.set_subject_name(&name).unwrap();
+        cert.set_issuer_name(&name).unwrap();
+        cert.set_pubkey(&key).unwrap();
+        cert.set_not_before(&Asn1Time::days_from_now(0).unwrap()).unwrap();
+        cert.set_not_after(&Asn1Time::days_from_now(1).unwrap()).unwrap();
+        cert.sign(&key, MessageDigest::sha256()).unwrap();
+        (key, cert.build().to_pem().unwrap())
+    }
+
+    fn signed(key: &PKey<Private>, mut message: Value) -> Vec<u8> {
+        message["SignatureVersion"] = json!("2");
+        message["SigningCertURL"] = json!(CERT_URL);
+        message["Signature"] = json!("");
+        let unsigned: SnsMessage = serde_json::from_value(message.clone()).unwrap();
+        let mut signer = Signer::new(MessageDigest::sha256(), key).unwrap();
+        signer.update(unsigned.string_to_sign().unwrap().as_bytes()).unwrap();
+        message["Signature"] = json!(base64::engine::general_purpose::STANDARD.encode(signer.sign_to_vec().unwrap()));
+        serde_json::to_vec(&message).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_sns_deliveries_are_verified() {
+        let (key, pem) = signing_key();
+        let sns = Arc::new(FakeSns { pem, ..Default::default() });
+        let Fixture { tenant, discovered_at, service, .. } = setup().await;
+        let service = service.with_sns_transport(sns.clone());
+        let new_source = NewEventSource { description: Some("prod".into()), topic_arns: vec![TOPIC.into()] };
+        let (source, _) = service.create_source(tenant, new_source, discovered_at).await.unwrap();
+        let subscribe_url = "https://sns.us-east-1.amazonaws.com/?Action=ConfirmSubscription&Token=abc";
+        let confirmation = signed(&key, json!({
+            "Type": "SubscriptionConfirmation", "MessageId": "m-1", "Token": "abc", "TopicArn": TOPIC,
+            "Message": "You have chosen to subscribe", "SubscribeURL": subscribe_url, "Timestamp": "2025-07-21T06:00:00.000Z",
+        }));
+        assert_eq!(service.receive_sns(source.id, &confirmation, discovered_at).await.unwrap(), EventOutcome::SubscriptionConfirmed);
+        assert_eq!(*sns.confirmed.lock().unwrap(), vec![subscribe_url.to_string()]);
+
+        let event = eventbridge("ev-9", "aws.ec2", "EC2 Instance State-change Notification", discovered_at + Duration::minutes(1), json!({"instance-id": "i-0web", "state": "stopping"}));
+        let notification = json!({
+            "Type": "Notification", "MessageId": "m-2", "TopicArn": TOPIC, "Message": event.to_string(),
+            "Timestamp": "2025-07-21T06:01:00.000Z",
+        });
+        let mut tampered: Value = serde_json::from_slice(&signed(&key, notification.clone())).unwrap();
+        tampered["Message"] = json!(event.to_string().replace("stopping", "terminated"));
+        let tampered = serde_json::to_vec(&tampered).unwrap();
+        assert!(matches!(service.receive_sns(source.id, &tampered, discovered_at).await, Err(AppError::Auth(_))));
+        let mut other_topic = notification.clone();
+        other_topic["TopicArn"] = json!("arn:aws:sns:us-east-1:999999999999:elsewhere");
+        let other_topic = signed(&key, other_topic);
+        assert!(matches!(service.receive_sns(source.id, &other_topic, discovered_at).await, Err(AppError::Forbidden(_))));
+
+        let outcome = service.receive_sns(source.id, &signed(&key, notification), discovered_at).await.unwrap();
+        let EventOutcome::Applied(applied) = outcome else { panic!("expected the event to apply") };
+        assert_eq!(applied.after.unwrap().state.as_deref(), Some("stopping"));
+    }
+}
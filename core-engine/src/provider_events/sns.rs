@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration as StdDuration;
+
+use axum::async_trait;
+use base64::Engine;
+use openssl::hash::MessageDigest;
+use openssl::sign::Verifier;
+use openssl::x509::X509;
+use serde::Deserialize;
+
+use crate::error::{AppError, AppResult};
+
+pub const MESSAGE_TYPE_HEADER: &str = "x-amz-sns-message-type";
+
+const NOTIFICATION: &str = "Notification";
+const SUBSCRIPTION_CONFIRMATION: &str = "SubscriptionConfirmation";
+const UNSUBSCRIBE_CONFIRMATION: &str = "UnsubscribeConfirmation";
+
+// An SNS HTTP(S) delivery; for notifications `message` is the EventBridge event
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SnsMessage {
+    #[serde(rename = "Type")]
+    pub message_type: String,
+    pub message_id: String,
+    pub topic_arn: String,
+    #[serde(default)]
+    pub subject: Option<String>,
+    pub message: String,
+    pub timestamp: String,
+    pub signature_version: String,
+    pub signature: String,
+    #[serde(rename = "SigningCertURL")]
+    pub signing_cert_url: String,
+    #[serde(default, rename = "SubscribeURL")]
+    pub subscribe_url: Option<String>,
+    #[serde(default)]
+    pub token: Option<String>,
+}
+
+impl SnsMessage {
+    pub fn is_notification(&self) -> bool {
+        self.message_type == NOTIFICATION
+    }
+
+    pub fn is_subscription_confirmation(&self) -> bool {
+        self.message_type == SUBSCRIPTION_CONFIRMATION
+    }
+
+    // The fields SNS signs, as `<name>\n<value>\n` in this order
+    pub fn string_to_sign(&self) -> AppResult<String> {
+        let confirmation = match self.message_type.as_str() {
+            NOTIFICATION => false,
+            SUBSCRIPTION_CONFIRMATION | UNSUBSCRIBE_CONFIRMATION => true,
+            other => return Err(AppError::Validation(format!("Unknown SNS message type '{}'", other))),
+        };
+        let missing = |field: &str| AppError::Validation(format!("SNS {} is missing {}", self.message_type, field));
+        let mut fields = vec![("Message", self.message.as_str()), ("MessageId", self.message_id.as_str())];
+        if confirmation {
+            fields.push(("SubscribeURL", self.subscribe_url.as_deref().ok_or_else(|| missing("SubscribeURL"))?));
+        } else if let Some(subject) = &self.subject {
+            fields.push(("Subject", subject));
+        }
+        fields.push(("Timestamp", &self.timestamp));
+        if confirmation {
+            fields.push(("Token", self.token.as_deref().ok_or_else(|| missing("Token"))?));
+        }
+        fields.push(("TopicArn", &self.topic_arn));
+        fields.push(("Type", &self.message_type));
+        Ok(fields.into_iter().map(|(name, value)| format!("{}\n{}\n", name, value)).collect())
+    }
+}
+
+// Only SNS itself may serve signing certificates and subscription links
+fn validate_sns_url(url: &str, what: &str) -> AppResult<reqwest::Url> {
+    let parsed = reqwest::Url::parse(url).map_err(|_| AppError::Auth(format!("Invalid SNS {}", what)))?;
+    let from_sns = parsed.host_str().is_some_and(|host| {
+        let region = host.strip_suffix(".amazonaws.com").or_else(|| host.strip_suffix(".amazonaws.com.cn"));
+        region.and_then(|r| r.strip_prefix("sns.")).is_some_and(|r| !r.is_empty() && !r.contains('.'))
+    });
+    if parsed.scheme() != "https" || !from_sns {
+        return Err(AppError::Auth(format!("SNS {} '{}' is not served by SNS", what, url)));
+    }
+    Ok(parsed)
+}
+
+#[async_trait]
+pub trait SnsTransport: Send + Sync {
+    // PEM bytes
+    async fn signing_cert(&self, url: &str) -> AppResult<Vec<u8>>;
+    async fn confirm_subscription(&self, subscribe_url: &str) -> AppResult<()>;
+}
+
+pub struct HttpSnsTransport {
+    client: reqwest::Client,
+}
+
+impl HttpSnsTransport {
+    pub fn new(timeout: StdDuration) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .redirect(reqwest::redirect::Policy::none())
+            .user_agent("sirsi-provider-events")
+            .build()
+            .unwrap_or_default();
+        Self { client }
+    }
+
+    async fn get(&self, url: &str) -> AppResult<reqwest::Response> {
+        self.client
+            .get(url)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(|e| AppError::ExternalService(format!("SNS request failed: {}", e)))
+    }
+}
+
+impl Default for HttpSnsTransport {
+    fn default() -> Self {
+        Self::new(StdDuration::from_secs(10))
+    }
+}
+
+#[async_trait]
+impl SnsTransport for HttpSnsTransport {
+    async fn signing_cert(&self, url: &str) -> AppResult<Vec<u8>> {
+        let body = self.get(url).await?.bytes().await;
+        body.map(|b| b.to_vec()).map_err(|e| AppError::ExternalService(format!("SNS request failed: {}", e)))
+    }
+
+    async fn confirm_subscription(&self, subscribe_url: &str) -> AppResult<()> {
+        self.get(subscribe_url).await.map(|_| ())
+    }
+}
+
+pub struct SnsVerifier {
+    transport: Arc<dyn SnsTransport>,
+    // SNS rotates certificates rarely; each URL is fetched once per process
+    certs: Mutex<HashMap<String, X509>>,
+}
+
+impl SnsVerifier {
+    pub fn new(transport: Arc<dyn SnsTransport>) -> Self {
+        Self { transport, certs: Mutex::new(HashMap::new()) }
+    }
+
+    async fn cert(&self, url: &str) -> AppResult<X509> {
+        if let Some(cert) = self.certs.lock().unwrap().get(url) {
+            return Ok(cert.clone());
+        }
+        validate_sns_url(url, "signing certificate URL")?;
+        let pem = self.transport.signing_cert(url).await?;
+        let cert = X509::from_pem(&pem).map_err(|e| AppError::Auth(format!("Invalid SNS signing certificate: {}", e)))?;
+        self.certs.lock().unwrap().insert(url.to_string(), cert.clone());
+        Ok(cert)
+    }
+
+    pub async fn verify(&self, message: &SnsMessage) -> AppResult<()> {
+        let digest = match message.signature_version.as_str() {
+            "1" => MessageDigest::sha1(),
+            "2" => MessageDigest::sha256(),
+            other => return Err(AppError::Auth(format!("Unsupported SNS signature version '{}'", other))),
+        };
+        let signature = base64::engine::general_purpose::STANDARD
+            .decode(&message.signature)
+            .map_err(|_| AppError::Auth("SNS signature is not base64".into()))?;
+        let signed = message.string_to_sign()?;
+        let cert = self.cert(&message.signing_cert_url).await?;
+        let invalid = |e: openssl::error::ErrorStack| AppError::Auth(format!("SNS signature check failed: {}", e));
+        let key = cert.public_key().map_err(invalid)?;
+        let mut verifier = Verifier::new(digest, &key).map_err(invalid)?;
+        verifier.update(signed.as_bytes()).map_err(invalid)?;
+        if !verifier.verify(&signature).map_err(invalid)? {
+            return Err(AppError::Auth("SNS signature does not match".into()));
+        }
+        Ok(())
+    }
+
+    // Call after `verify`; SNS starts delivering once the subscribe link has been visited
+    pub async fn confirm(&self, message: &SnsMessage) -> AppResult<()> {
+        let url = message
+            .subscribe_url
+            .as_deref()
+            .ok_or_else(|| AppError::Validation("SNS SubscriptionConfirmation is missing SubscribeURL".into()))?;
+        validate_sns_url(url, "subscribe URL")?;
+        self.transport.confirm_subscription(url).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_string_to_sign_and_url_checks() {
+        let message: SnsMessage = serde_json::from_value(serde_json::json!({
+            "Type": "SubscriptionConfirmation",
+            "MessageId": "165545c9-2a5c-472c-8df2-7ff2be2b3b1b",
+            "Token": "2336412f37",
+            "TopicArn": "arn:aws:sns:us-west-2:123456789012:MyTopic",
+            "Message": "You have chosen to subscribe to the topic",
+            "SubscribeURL": "https://sns.us-west-2.amazonaws.com/?Action=ConfirmSubscription",
+            "Timestamp": "2012-04-26T20:45:04.751Z",
+            "SignatureVersion": "1",
+            "Signature": "",
+            "SigningCertURL": "https://sns.us-west-2.amazonaws.com/SimpleNotificationService-f3ecfb7224c7233fe7bb5f59f96de52f.pem"
+        }))
+        .unwrap();
+        assert_eq!(
+            message.string_to_sign().unwrap(),
+            "Message\nYou have chosen to subscribe to the topic\nMessageId\n165545c9-2a5c-472c-8df2-7ff2be2b3b1b\n\
+             SubscribeURL\nhttps://sns.us-west-2.amazonaws.com/?Action=ConfirmSubscription\nTimestamp\n2012-04-26T20:45:04.751Z\n\
+             Token\n2336412f37\nTopicArn\narn:aws:sns:us-west-2:123456789012:MyTopic\nType\nSubscriptionConfirmation\n"
+        );
+        assert!(validate_sns_url(&message.signing_cert_url, "cert").is_ok());
+        assert!(validate_sns_url("https://sns.cn-north-1.amazonaws.com.cn/cert.pem", "cert").is_ok());
+        for url in [
+            "http://sns.us-west-2.amazonaws.com/cert.pem",
+            "https://sns.us-west-2.amazonaws.com.evil.example/cert.pem",
+            "https://evil.example/sns.us-west-2.amazonaws.com/cert.pem",
+            "https://s3.amazonaws.com/cert.pem",
+        ] {
+            assert!(validate_sns_url(url, "cert").is_err(), "{}", url);
+        }
+    }
+}
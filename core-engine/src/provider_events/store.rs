@@ -0,0 +1,318 @@
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use sqlx::types::Json;
+use sqlx::PgPool; // CockroachDB uses PostgreSQL protocol
+use uuid::Uuid;
+
+use super::{EventSource, ProviderEventStore, UnmappedEvent, UnmappedType};
+use crate::error::{AppError, AppResult};
+
+#[derive(sqlx::FromRow)]
+struct SourceRow {
+    id: Uuid,
+    tenant_id: Uuid,
+    description: Option<String>,
+    token_hash: String,
+    topic_arns: Json<Vec<String>>,
+    created_at: DateTime<Utc>,
+}
+
+impl From<SourceRow> for EventSource {
+    fn from(row: SourceRow) -> Self {
+        Self {
+            id: row.id,
+            tenant_id: row.tenant_id,
+            description: row.description,
+            token_hash: row.token_hash,
+            topic_arns: row.topic_arns.0,
+            created_at: row.created_at,
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct UnmappedRow {
+    id: Uuid,
+    tenant_id: Uuid,
+    source_id: Uuid,
+    event_id: String,
+    event_type: String,
+    payload: Json<Value>,
+    received_at: DateTime<Utc>,
+}
+
+impl From<UnmappedRow> for UnmappedEvent {
+    fn from(row: UnmappedRow) -> Self {
+        Self {
+            id: row.id,
+            tenant_id: row.tenant_id,
+            source_id: row.source_id,
+            event_id: row.event_id,
+            event_type: row.event_type,
+            payload: row.payload.0,
+            received_at: row.received_at,
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct UnmappedTypeRow {
+    event_type: String,
+    count: i64,
+    first_seen_at: DateTime<Utc>,
+    last_seen_at: DateTime<Utc>,
+}
+
+impl From<UnmappedTypeRow> for UnmappedType {
+    fn from(row: UnmappedTypeRow) -> Self {
+        Self { event_type: row.event_type, count: row.count, first_seen_at: row.first_seen_at, last_seen_at: row.last_seen_at }
+    }
+}
+
+const SOURCE_COLUMNS: &str = "id, tenant_id, description, token_hash, topic_arns, created_at";
+
+pub struct PgProviderEventStore {
+    pool: PgPool,
+}
+
+impl PgProviderEventStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl ProviderEventStore for PgProviderEventStore {
+    async fn create_source(&self, source: &EventSource) -> AppResult<()> {
+        sqlx::query(&format!("INSERT INTO provider_event_sources ({}) VALUES ($1, $2, $3, $4, $5, $6)", SOURCE_COLUMNS))
+            .bind(source.id)
+            .bind(source.tenant_id)
+            .bind(&source.description)
+            .bind(&source.token_hash)
+            .bind(Json(&source.topic_arns))
+            .bind(source.created_at)
+            .execute(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(())
+    }
+
+    async fn source(&self, id: Uuid) -> AppResult<Option<EventSource>> {
+        let row = sqlx::query_as::<_, SourceRow>(&format!("SELECT {} FROM provider_event_sources WHERE id = $1", SOURCE_COLUMNS))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(row.map(EventSource::from))
+    }
+
+    async fn sources(&self, tenant_id: Uuid) -> AppResult<Vec<EventSource>> {
+        let rows = sqlx::query_as::<_, SourceRow>(&format!(
+            "SELECT {} FROM provider_event_sources WHERE tenant_id = $1 ORDER BY created_at",
+            SOURCE_COLUMNS
+        ))
+        .bind(tenant_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(rows.into_iter().map(EventSource::from).collect())
+    }
+
+    async fn delete_source(&self, tenant_id: Uuid, id: Uuid) -> AppResult<bool> {
+        let result = sqlx::query("DELETE FROM provider_event_sources WHERE tenant_id = $1 AND id = $2")
+            .bind(tenant_id)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn claim(&self, tenant_id: Uuid, event_id: &str, now: DateTime<Utc>) -> AppResult<bool> {
+        let result = sqlx::query(
+            r#"INSERT INTO provider_events_processed (tenant_id, event_id, received_at) VALUES ($1, $2, $3)
+            ON CONFLICT (tenant_id, event_id) DO NOTHING"#
+        )
+        .bind(tenant_id)
+        .bind(event_id)
+        .bind(now)
+        .execute(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn release(&self, tenant_id: Uuid, event_id: &str) -> AppResult<()> {
+        sqlx::query("DELETE FROM provider_events_processed WHERE tenant_id = $1 AND event_id = $2")
+            .bind(tenant_id)
+            .bind(event_id)
+            .execute(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(())
+    }
+
+    async fn record_unmapped(&self, event: &UnmappedEvent) -> AppResult<()> {
+        let mut tx = self.pool.begin().await.map_err(AppError::Database)?;
+        sqlx::query(
+            r#"INSERT INTO provider_events_unmapped (id, tenant_id, source_id, event_id, event_type, payload, received_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)"#
+        )
+        .bind(event.id)
+        .bind(event.tenant_id)
+        .bind(event.source_id)
+        .bind(&event.event_id)
+        .bind(&event.event_type)
+        .bind(Json(&event.payload))
+        .bind(event.received_at)
+        .execute(&mut *tx)
+        .await
+        .map_err(AppError::Database)?;
+        sqlx::query(
+            r#"INSERT INTO provider_event_unmapped_types (tenant_id, event_type, count, first_seen_at, last_seen_at)
+            VALUES ($1, $2, 1, $3, $3)
+            ON CONFLICT (tenant_id, event_type) DO UPDATE SET
+            count = provider_event_unmapped_types.count + 1, last_seen_at = excluded.last_seen_at"#
+        )
+        .bind(event.tenant_id)
+        .bind(&event.event_type)
+        .bind(event.received_at)
+        .execute(&mut *tx)
+        .await
+        .map_err(AppError::Database)?;
+        tx.commit().await.map_err(AppError::Database)?;
+
+        Ok(())
+    }
+
+    async fn unmapped_types(&self, tenant_id: Uuid) -> AppResult<Vec<UnmappedType>> {
+        let rows = sqlx::query_as::<_, UnmappedTypeRow>(
+            r#"SELECT event_type, count, first_seen_at, last_seen_at FROM provider_event_unmapped_types
+            WHERE tenant_id = $1 ORDER BY count DESC, event_type"#
+        )
+        .bind(tenant_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(rows.into_iter().map(UnmappedType::from).collect())
+    }
+
+    async fn unmapped_events(&self, tenant_id: Uuid, event_type: &str, limit: i64) -> AppResult<Vec<UnmappedEvent>> {
+        let rows = sqlx::query_as::<_, UnmappedRow>(
+            r#"SELECT id, tenant_id, source_id, event_id, event_type, payload, received_at FROM provider_events_unmapped
+            WHERE tenant_id = $1 AND event_type = $2 ORDER BY received_at DESC LIMIT $3"#
+        )
+        .bind(tenant_id)
+        .bind(event_type)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(rows.into_iter().map(UnmappedEvent::from).collect())
+    }
+}
+
+#[derive(Default)]
+struct ProviderEventState {
+    sources: Vec<EventSource>,
+    processed: HashSet<(Uuid, String)>,
+    unmapped: Vec<UnmappedEvent>,
+    types: HashMap<(Uuid, String), UnmappedType>,
+}
+
+#[derive(Default)]
+pub struct InMemoryProviderEventStore {
+    state: Mutex<ProviderEventState>,
+}
+
+impl InMemoryProviderEventStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl ProviderEventStore for InMemoryProviderEventStore {
+    async fn create_source(&self, source: &EventSource) -> AppResult<()> {
+        self.state.lock().unwrap().sources.push(source.clone());
+        Ok(())
+    }
+
+    async fn source(&self, id: Uuid) -> AppResult<Option<EventSource>> {
+        Ok(self.state.lock().unwrap().sources.iter().find(|s| s.id == id).cloned())
+    }
+
+    async fn sources(&self, tenant_id: Uuid) -> AppResult<Vec<EventSource>> {
+        let state = self.state.lock().unwrap();
+        Ok(state.sources.iter().filter(|s| s.tenant_id == tenant_id).cloned().collect())
+    }
+
+    async fn delete_source(&self, tenant_id: Uuid, id: Uuid) -> AppResult<bool> {
+        let mut state = self.state.lock().unwrap();
+        let before = state.sources.len();
+        state.sources.retain(|s| !(s.tenant_id == tenant_id && s.id == id));
+        Ok(state.sources.len() < before)
+    }
+
+    async fn claim(&self, tenant_id: Uuid, event_id: &str, _now: DateTime<Utc>) -> AppResult<bool> {
+        Ok(self.state.lock().unwrap().processed.insert((tenant_id, event_id.to_string())))
+    }
+
+    async fn release(&self, tenant_id: Uuid, event_id: &str) -> AppResult<()> {
+        self.state.lock().unwrap().processed.remove(&(tenant_id, event_id.to_string()));
+        Ok(())
+    }
+
+    async fn record_unmapped(&self, event: &UnmappedEvent) -> AppResult<()> {
+        let mut state = self.state.lock().unwrap();
+        state.unmapped.push(event.clone());
+        state
+            .types
+            .entry((event.tenant_id, event.event_type.clone()))
+            .and_modify(|t| {
+                t.count += 1;
+                t.last_seen_at = event.received_at;
+            })
+            .or_insert_with(|| UnmappedType {
+                event_type: event.event_type.clone(),
+                count: 1,
+                first_seen_at: event.received_at,
+                last_seen_at: event.received_at,
+            });
+        Ok(())
+    }
+
+    async fn unmapped_types(&self, tenant_id: Uuid) -> AppResult<Vec<UnmappedType>> {
+        let state = self.state.lock().unwrap();
+        let mut types: Vec<UnmappedType> =
+            state.types.iter().filter(|((t, _), _)| *t == tenant_id).map(|(_, t)| t.clone()).collect();
+        types.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.event_type.cmp(&b.event_type)));
+        Ok(types)
+    }
+
+    async fn unmapped_events(&self, tenant_id: Uuid, event_type: &str, limit: i64) -> AppResult<Vec<UnmappedEvent>> {
+        let state = self.state.lock().unwrap();
+        let mut events: Vec<UnmappedEvent> = state
+            .unmapped
+            .iter()
+            .filter(|e| e.tenant_id == tenant_id && e.event_type == event_type)
+            .cloned()
+            .collect();
+        events.sort_by_key(|e| Reverse(e.received_at));
+        events.truncate(limit.max(0) as usize);
+        Ok(events)
+    }
+}
@@ -0,0 +1,805 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration as StdDuration;
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::compliance::{ComplianceService, Finding, FindingFilter, FindingStatus, Program};
+use crate::error::{AppError, AppResult};
+use crate::inventory::{InventoryResource, InventoryService};
+
+pub mod store;
+
+pub use store::{InMemoryRemediationStore, PgRemediationStore};
+
+// Names parameter bindings can refer to
+const BINDING_VARIABLES: &[&str] = &["resource", "finding"];
+// Resources carrying this tag are never remediated, whatever its value
+pub const DEFAULT_EXCLUSION_TAG: &str = "sirsi:remediation-exclude";
+const DEFAULT_MAX_CONCURRENT: u32 = 5;
+// Open findings considered per auto-remediating rule on each worker pass
+const AUTO_CANDIDATES: i64 = 1000;
+
+fn default_max_concurrent() -> u32 {
+    DEFAULT_MAX_CONCURRENT
+}
+
+// Links a compliance rule to the workflow template that fixes its findings. Each parameter is a
+// CEL expression over the finding's `resource` (as inventoried) and the `finding` itself.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RemediationMapping {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub rule_key: String,
+    pub template_id: String,
+    pub parameters: BTreeMap<String, String>,
+    pub auto_remediate: bool,
+    // Remediations of this rule running at once, counting manual ones
+    pub max_concurrent: u32,
+    pub created_by: Uuid,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewRemediationMapping {
+    pub rule_key: String,
+    pub template_id: String,
+    #[serde(default)]
+    pub parameters: BTreeMap<String, String>,
+    #[serde(default)]
+    pub auto_remediate: bool,
+    #[serde(default = "default_max_concurrent")]
+    pub max_concurrent: u32,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct MappingUpdate {
+    pub auto_remediate: Option<bool>,
+    pub max_concurrent: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RemediationTrigger {
+    Operator { user_id: Uuid, reason: Option<String> },
+    Auto { mapping_id: Uuid },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RemediationStatus {
+    Running,
+    // The workflow succeeded and a rescan of the resource no longer sees the violation
+    Verified,
+    // The workflow succeeded but the rescan still sees the violation
+    Unverified,
+    Failed,
+}
+
+impl RemediationStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RemediationStatus::Running => "running",
+            RemediationStatus::Verified => "verified",
+            RemediationStatus::Unverified => "unverified",
+            RemediationStatus::Failed => "failed",
+        }
+    }
+
+    pub fn parse(value: &str) -> AppResult<Self> {
+        serde_json::from_value(Value::String(value.to_string()))
+            .map_err(|_| AppError::Validation(format!("Unknown remediation status '{}'", value)))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Remediation {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub finding_id: Uuid,
+    pub mapping_id: Uuid,
+    pub rule_key: String,
+    pub template_id: String,
+    pub parameters: BTreeMap<String, Value>,
+    pub trigger: RemediationTrigger,
+    pub run_id: Option<String>,
+    pub status: RemediationStatus,
+    pub detail: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+// Append-only; together with `trigger` this is the remediation's audit trail
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RemediationEvent {
+    pub remediation_id: Uuid,
+    pub tenant_id: Uuid,
+    pub kind: String,
+    pub detail: Value,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RemediationDetail {
+    #[serde(flatten)]
+    pub remediation: Remediation,
+    pub events: Vec<RemediationEvent>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WorkflowOutcome {
+    Running,
+    Succeeded,
+    Failed(String),
+}
+
+// Starts remediation workflows from templates, e.g. through the automation template library
+#[async_trait]
+pub trait WorkflowExecutor: Send + Sync {
+    // `idempotency_key` is the remediation id, so a retried start runs the workflow once
+    async fn start(&self, template_id: &str, parameters: &BTreeMap<String, Value>, idempotency_key: &str) -> AppResult<String>;
+    async fn outcome(&self, run_id: &str) -> AppResult<WorkflowOutcome>;
+}
+
+// The state of a finding's resource; None when it no longer exists
+#[async_trait]
+pub trait ResourceLookup: Send + Sync {
+    async fn resource(&self, finding: &Finding) -> AppResult<Option<InventoryResource>>;
+}
+
+// What the last discovery run saw. Verification needs a rescanner that asks the provider.
+#[async_trait]
+impl ResourceLookup for InventoryService {
+    async fn resource(&self, finding: &Finding) -> AppResult<Option<InventoryResource>> {
+        InventoryService::resource(
+            self,
+            finding.tenant_id,
+            &finding.provider,
+            &finding.account_id,
+            &finding.resource_key,
+            &finding.region,
+        )
+        .await
+    }
+}
+
+#[async_trait]
+pub trait RemediationStore: Send + Sync {
+    async fn create_mapping(&self, mapping: &RemediationMapping) -> AppResult<()>;
+    async fn update_mapping(&self, mapping: &RemediationMapping) -> AppResult<()>;
+    async fn mappings(&self, tenant_id: Uuid) -> AppResult<Vec<RemediationMapping>>;
+    async fn mapping(&self, tenant_id: Uuid, id: Uuid) -> AppResult<Option<RemediationMapping>>;
+    async fn mapping_for_rule(&self, tenant_id: Uuid, rule_key: &str) -> AppResult<Option<RemediationMapping>>;
+    async fn delete_mapping(&self, tenant_id: Uuid, id: Uuid) -> AppResult<bool>;
+    // Across tenants
+    async fn auto_mappings(&self) -> AppResult<Vec<RemediationMapping>>;
+    async fn create_remediation(&self, remediation: &Remediation) -> AppResult<()>;
+    async fn update_remediation(&self, remediation: &Remediation) -> AppResult<()>;
+    async fn remediation(&self, tenant_id: Uuid, id: Uuid) -> AppResult<Option<Remediation>>;
+    async fn remediations(&self, tenant_id: Uuid, finding_id: Option<Uuid>, limit: i64) -> AppResult<Vec<Remediation>>;
+    // Across tenants
+    async fn running(&self) -> AppResult<Vec<Remediation>>;
+    async fn running_count(&self, mapping_id: Uuid) -> AppResult<u32>;
+    async fn record_event(&self, event: &RemediationEvent) -> AppResult<()>;
+    async fn events(&self, tenant_id: Uuid, remediation_id: Uuid) -> AppResult<Vec<RemediationEvent>>;
+}
+
+pub struct RemediationService {
+    store: Arc<dyn RemediationStore>,
+    compliance: Arc<ComplianceService>,
+    executor: Option<Arc<dyn WorkflowExecutor>>,
+    inventory: Option<Arc<dyn ResourceLookup>>,
+    rescanner: Option<Arc<dyn ResourceLookup>>,
+    exclusion_tag: String,
+}
+
+impl RemediationService {
+    pub fn new(store: Arc<dyn RemediationStore>, compliance: Arc<ComplianceService>) -> Self {
+        Self {
+            store,
+            compliance,
+            executor: None,
+            inventory: None,
+            rescanner: None,
+            exclusion_tag: DEFAULT_EXCLUSION_TAG.to_string(),
+        }
+    }
+
+    pub fn with_executor(mut self, executor: Arc<dyn WorkflowExecutor>) -> Self {
+        self.executor = Some(executor);
+        self
+    }
+
+    // Where parameter bindings and the exclusion tag are read from
+    pub fn with_inventory(mut self, inventory: Arc<dyn ResourceLookup>) -> Self {
+        self.inventory = Some(inventory);
+        self
+    }
+
+    // Fetches one resource's current state from its provider to verify a remediation
+    pub fn with_rescanner(mut self, rescanner: Arc<dyn ResourceLookup>) -> Self {
+        self.rescanner = Some(rescanner);
+        self
+    }
+
+    pub fn with_exclusion_tag(mut self, tag: impl Into<String>) -> Self {
+        self.exclusion_tag = tag.into();
+        self
+    }
+
+    pub async fn create_mapping(
+        &self,
+        tenant_id: Uuid,
+        mapping: NewRemediationMapping,
+        created_by: Uuid,
+        now: DateTime<Utc>,
+    ) -> AppResult<RemediationMapping> {
+        self.compliance.rule(tenant_id, &mapping.rule_key).await?;
+        if mapping.template_id.trim().is_empty() {
+            return Err(AppError::Validation("template_id is required".into()));
+        }
+        validate_max_concurrent(mapping.max_concurrent)?;
+        for (name, expression) in &mapping.parameters {
+            if name.trim().is_empty() {
+                return Err(AppError::Validation("Parameter names must not be blank".into()));
+            }
+            Program::parse(expression, BINDING_VARIABLES)
+                .map_err(|e| AppError::Validation(format!("Parameter '{}': {}", name, e)))?;
+        }
+        if self.store.mapping_for_rule(tenant_id, &mapping.rule_key).await?.is_some() {
+            return Err(AppError::Conflict {
+                message: format!("Rule '{}' already has a remediation mapping", mapping.rule_key),
+                current: None,
+            });
+        }
+        let mapping = RemediationMapping {
+            id: Uuid::new_v4(),
+            tenant_id,
+            rule_key: mapping.rule_key,
+            template_id: mapping.template_id,
+            parameters: mapping.parameters,
+            auto_remediate: mapping.auto_remediate,
+            max_concurrent: mapping.max_concurrent,
+            created_by,
+            created_at: now,
+        };
+        self.store.create_mapping(&mapping).await?;
+        Ok(mapping)
+    }
+
+    pub async fn mappings(&self, tenant_id: Uuid) -> AppResult<Vec<RemediationMapping>> {
+        self.store.mappings(tenant_id).await
+    }
+
+    pub async fn update_mapping(&self, tenant_id: Uuid, id: Uuid, update: MappingUpdate) -> AppResult<RemediationMapping> {
+        let mut mapping = self
+            .store
+            .mapping(tenant_id, id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Remediation mapping not found".into()))?;
+        if let Some(max_concurrent) = update.max_concurrent {
+            validate_max_concurrent(max_concurrent)?;
+            mapping.max_concurrent = max_concurrent;
+        }
+        if let Some(auto_remediate) = update.auto_remediate {
+            mapping.auto_remediate = auto_remediate;
+        }
+        self.store.update_mapping(&mapping).await?;
+        Ok(mapping)
+    }
+
+    pub async fn delete_mapping(&self, tenant_id: Uuid, id: Uuid) -> AppResult<()> {
+        if !self.store.delete_mapping(tenant_id, id).await? {
+            return Err(AppError::NotFound("Remediation mapping not found".into()));
+        }
+        Ok(())
+    }
+
+    pub async fn remediations(&self, tenant_id: Uuid, finding_id: Option<Uuid>, limit: i64) -> AppResult<Vec<Remediation>> {
+        self.store.remediations(tenant_id, finding_id, limit).await
+    }
+
+    pub async fn remediation(&self, tenant_id: Uuid, id: Uuid) -> AppResult<RemediationDetail> {
+        let remediation = self
+            .store
+            .remediation(tenant_id, id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Remediation not found".into()))?;
+        let events = self.store.events(tenant_id, id).await?;
+        Ok(RemediationDetail { remediation, events })
+    }
+
+    // An operator fixing one finding; the rule's concurrency cap still applies
+    pub async fn remediate(
+        &self,
+        tenant_id: Uuid,
+        finding_id: Uuid,
+        user_id: Uuid,
+        reason: Option<String>,
+        now: DateTime<Utc>,
+    ) -> AppResult<Remediation> {
+        let finding = self.compliance.finding(tenant_id, finding_id).await?;
+        let mapping = self
+            .store
+            .mapping_for_rule(tenant_id, &finding.rule_key)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("No remediation is mapped to rule '{}'", finding.rule_key)))?;
+        let running = self.store.running_count(mapping.id).await?;
+        if running >= mapping.max_concurrent {
+            return Err(AppError::QuotaExceeded {
+                quota: format!("concurrent remediations of rule '{}'", mapping.rule_key),
+                limit: mapping.max_concurrent.into(),
+                used: running.into(),
+            });
+        }
+        let resource = self.current_resource(&finding).await?;
+        if self.excluded(&resource) {
+            return Err(AppError::Forbidden(format!(
+                "Resource is excluded from remediation by its '{}' tag",
+                self.exclusion_tag
+            )));
+        }
+        self.start(&finding, &resource, &mapping, RemediationTrigger::Operator { user_id, reason }, now).await
+    }
+
+    async fn current_resource(&self, finding: &Finding) -> AppResult<InventoryResource> {
+        let inventory = self
+            .inventory
+            .as_ref()
+            .ok_or_else(|| AppError::Configuration("No inventory configured for remediation".into()))?;
+        inventory
+            .resource(finding)
+            .await?
+            .ok_or_else(|| AppError::NotFound("The finding's resource is no longer in the inventory".into()))
+    }
+
+    fn excluded(&self, resource: &InventoryResource) -> bool {
+        resource.tags.contains_key(&self.exclusion_tag)
+    }
+
+    async fn start(
+        &self,
+        finding: &Finding,
+        resource: &InventoryResource,
+        mapping: &RemediationMapping,
+        trigger: RemediationTrigger,
+        now: DateTime<Utc>,
+    ) -> AppResult<Remediation> {
+        let executor = self
+            .executor
+            .as_ref()
+            .ok_or_else(|| AppError::Configuration("No workflow executor configured for remediation".into()))?;
+        match finding.status {
+            FindingStatus::Open => {}
+            FindingStatus::Remediating => {
+                return Err(AppError::Conflict { message: "Finding is already being remediated".into(), current: None })
+            }
+            FindingStatus::Resolved => {
+                return Err(AppError::Conflict { message: "Finding is already resolved".into(), current: None })
+            }
+        }
+        let parameters = bind_parameters(mapping, resource, finding)?;
+        let mut remediation = Remediation {
+            id: Uuid::new_v4(),
+            tenant_id: finding.tenant_id,
+            finding_id: finding.id,
+            mapping_id: mapping.id,
+            rule_key: finding.rule_key.clone(),
+            template_id: mapping.template_id.clone(),
+            parameters,
+            trigger,
+            run_id: None,
+            status: RemediationStatus::Running,
+            detail: None,
+            started_at: now,
+            completed_at: None,
+        };
+        self.store.create_remediation(&remediation).await?;
+        self.event(&remediation, "triggered", json!({ "trigger": remediation.trigger, "parameters": remediation.parameters }), now)
+            .await?;
+        let remediation_id = remediation.id;
+        self.compliance
+            .update_finding(finding.tenant_id, finding.id, |f| {
+                f.status = FindingStatus::Remediating;
+                f.remediation_id = Some(remediation_id);
+            })
+            .await?;
+
+        match executor.start(&remediation.template_id, &remediation.parameters, &remediation.id.to_string()).await {
+            Ok(run_id) => {
+                remediation.run_id = Some(run_id.clone());
+                self.store.update_remediation(&remediation).await?;
+                self.event(&remediation, "workflow_started", json!({ "run_id": run_id }), now).await?;
+                Ok(remediation)
+            }
+            Err(e) => {
+                self.finish(&mut remediation, RemediationStatus::Failed, Some(e.to_string()), FindingStatus::Open, now)
+                    .await?;
+                Err(e)
+            }
+        }
+    }
+
+    async fn event(&self, remediation: &Remediation, kind: &str, detail: Value, now: DateTime<Utc>) -> AppResult<()> {
+        self.store
+            .record_event(&RemediationEvent {
+                remediation_id: remediation.id,
+                tenant_id: remediation.tenant_id,
+                kind: kind.to_string(),
+                detail,
+                created_at: now,
+            })
+            .await
+    }
+
+    // Closes the remediation and moves its finding on, unless a scan or a later remediation
+    // already has
+    async fn finish(
+        &self,
+        remediation: &mut Remediation,
+        status: RemediationStatus,
+        detail: Option<String>,
+        finding_status: FindingStatus,
+        now: DateTime<Utc>,
+    ) -> AppResult<()> {
+        remediation.status = status;
+        remediation.detail = detail.clone();
+        remediation.completed_at = Some(now);
+        self.store.update_remediation(remediation).await?;
+        self.event(remediation, status.as_str(), json!({ "detail": detail }), now).await?;
+        let remediation_id = remediation.id;
+        self.compliance
+            .update_finding(remediation.tenant_id, remediation.finding_id, |f| {
+                if f.status == FindingStatus::Remediating && f.remediation_id == Some(remediation_id) {
+                    f.status = finding_status;
+                    if finding_status == FindingStatus::Resolved {
+                        f.resolved_at = Some(now);
+                    }
+                }
+            })
+            .await?;
+        Ok(())
+    }
+
+    // Starts remediations for open findings of auto-remediating rules, up to each rule's cap
+    pub async fn auto_remediate(&self, now: DateTime<Utc>) -> AppResult<usize> {
+        let mut started = 0;
+        for mapping in self.store.auto_mappings().await? {
+            let running = self.store.running_count(mapping.id).await?;
+            let mut capacity = mapping.max_concurrent.saturating_sub(running);
+            if capacity == 0 {
+                continue;
+            }
+            let filter = FindingFilter { status: FindingStatus::Open, rule_key: Some(mapping.rule_key.clone()), severity: None };
+            for finding in self.compliance.findings(mapping.tenant_id, &filter, AUTO_CANDIDATES).await? {
+                let resource = match self.current_resource(&finding).await {
+                    Ok(resource) if !self.excluded(&resource) => resource,
+                    Ok(_) => continue,
+                    Err(e) => {
+                        warn!("Skipping auto-remediation of finding {}: {}", finding.id, e);
+                        continue;
+                    }
+                };
+                let trigger = RemediationTrigger::Auto { mapping_id: mapping.id };
+                match self.start(&finding, &resource, &mapping, trigger, now).await {
+                    Ok(_) => {
+                        started += 1;
+                        capacity -= 1;
+                    }
+                    Err(e) => warn!("Auto-remediation of finding {} failed to start: {}", finding.id, e),
+                }
+                if capacity == 0 {
+                    break;
+                }
+            }
+        }
+        Ok(started)
+    }
+
+    // Checks running workflows; a succeeded one is verified by rescanning just its resource
+    pub async fn poll(&self, now: DateTime<Utc>) -> AppResult<usize> {
+        let executor = self
+            .executor
+            .as_ref()
+            .ok_or_else(|| AppError::Configuration("No workflow executor configured for remediation".into()))?;
+        let mut finished = 0;
+        for mut remediation in self.store.running().await? {
+            let Some(run_id) = remediation.run_id.clone() else { continue };
+            match executor.outcome(&run_id).await {
+                Ok(WorkflowOutcome::Running) => continue,
+                Ok(WorkflowOutcome::Failed(reason)) => {
+                    self.finish(&mut remediation, RemediationStatus::Failed, Some(reason), FindingStatus::Open, now)
+                        .await?;
+                }
+                Ok(WorkflowOutcome::Succeeded) => {
+                    self.event(&remediation, "workflow_succeeded", json!({ "run_id": run_id }), now).await?;
+                    let (status, detail, finding_status) = match self.verify(&remediation).await {
+                        Ok(true) => (RemediationStatus::Verified, None, FindingStatus::Resolved),
+                        Ok(false) => (
+                            RemediationStatus::Unverified,
+                            Some("The rescan still sees the violation".to_string()),
+                            FindingStatus::Open,
+                        ),
+                        Err(e) => (RemediationStatus::Unverified, Some(format!("Rescan failed: {}", e)), FindingStatus::Open),
+                    };
+                    self.finish(&mut remediation, status, detail, finding_status, now).await?;
+                }
+                Err(e) => {
+                    warn!("Failed to check remediation run {}: {}", run_id, e);
+                    continue;
+                }
+            }
+            info!("Remediation {} of finding {} finished: {:?}", remediation.id, remediation.finding_id, remediation.status);
+            finished += 1;
+        }
+        Ok(finished)
+    }
+
+    async fn verify(&self, remediation: &Remediation) -> AppResult<bool> {
+        let rescanner = self
+            .rescanner
+            .as_ref()
+            .ok_or_else(|| AppError::Configuration("No rescanner configured to verify remediations".into()))?;
+        let finding = self.compliance.finding(remediation.tenant_id, remediation.finding_id).await?;
+        let rule = self.compliance.rule(remediation.tenant_id, &remediation.rule_key).await?;
+        match rescanner.resource(&finding).await? {
+            Some(resource) => Ok(!self.compliance.violates(&rule, &resource)?),
+            None => Ok(true),
+        }
+    }
+
+    pub fn spawn_worker(self: Arc<Self>, interval: StdDuration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = self.poll(Utc::now()).await {
+                    warn!("Failed to check running remediations: {}", e);
+                }
+                if let Err(e) = self.auto_remediate(Utc::now()).await {
+                    warn!("Failed to start auto-remediations: {}", e);
+                }
+            }
+        })
+    }
+}
+
+fn validate_max_concurrent(max_concurrent: u32) -> AppResult<()> {
+    if max_concurrent == 0 {
+        return Err(AppError::Validation("max_concurrent must be at least 1".into()));
+    }
+    Ok(())
+}
+
+fn bind_parameters(mapping: &RemediationMapping, resource: &InventoryResource, finding: &Finding) -> AppResult<BTreeMap<String, Value>> {
+    let resource = serde_json::to_value(resource).map_err(|e| AppError::Internal(e.to_string()))?;
+    let finding = serde_json::to_value(finding).map_err(|e| AppError::Internal(e.to_string()))?;
+    let variables = [("resource", resource), ("finding", finding)];
+    mapping
+        .parameters
+        .iter()
+        .map(|(name, expression)| {
+            let value = Program::parse(expression, BINDING_VARIABLES)
+                .and_then(|program| program.evaluate(&variables))
+                .map_err(|e| AppError::Validation(format!("Parameter '{}' could not be bound: {}", name, e)))?;
+            Ok((name.clone(), value))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compliance::InMemoryComplianceStore;
+    use crate::discovery::{DiscoveredResource, DiscoveryRun};
+    use crate::inventory::InMemoryInventoryStore;
+    use chrono::{Duration, TimeZone};
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    // Template, parameters and idempotency key of each started workflow
+    type Started = (String, BTreeMap<String, Value>, String);
+
+    #[derive(Default)]
+    struct FakeExecutor {
+        started: Mutex<Vec<Started>>,
+        outcomes: Mutex<HashMap<String, WorkflowOutcome>>,
+    }
+
+    impl FakeExecutor {
+        fn finish_all(&self, outcome: WorkflowOutcome) {
+            for current in self.outcomes.lock().unwrap().values_mut() {
+                if *current == WorkflowOutcome::Running {
+                    *current = outcome.clone();
+                }
+            }
+        }
+    }
+
+    #[async_trait]
+    impl WorkflowExecutor for FakeExecutor {
+        async fn start(&self, template_id: &str, parameters: &BTreeMap<String, Value>, idempotency_key: &str) -> AppResult<String> {
+            let mut started = self.started.lock().unwrap();
+            started.push((template_id.to_string(), parameters.clone(), idempotency_key.to_string()));
+            let run_id = format!("run-{}", started.len());
+            self.outcomes.lock().unwrap().insert(run_id.clone(), WorkflowOutcome::Running);
+            Ok(run_id)
+        }
+
+        async fn outcome(&self, run_id: &str) -> AppResult<WorkflowOutcome> {
+            Ok(self.outcomes.lock().unwrap()[run_id].clone())
+        }
+    }
+
+    // Answers with whatever the provider would report for each resource key
+    #[derive(Default)]
+    struct FakeRescanner(Mutex<HashMap<String, InventoryResource>>);
+
+    #[async_trait]
+    impl ResourceLookup for FakeRescanner {
+        async fn resource(&self, finding: &Finding) -> AppResult<Option<InventoryResource>> {
+            Ok(self.0.lock().unwrap().get(&finding.resource_key).cloned())
+        }
+    }
+
+    struct Fixture {
+        tenant: Uuid,
+        inventory: Arc<InventoryService>,
+        compliance: Arc<ComplianceService>,
+        executor: Arc<FakeExecutor>,
+        rescanner: Arc<FakeRescanner>,
+        service: RemediationService,
+        at: DateTime<Utc>,
+    }
+
+    fn group(id: &str, cidr: &str) -> DiscoveredResource {
+        let ingress = json!([{"cidr": cidr, "from_port": "22", "to_port": "22"}]);
+        DiscoveredResource::new("ec2:security-group", id, None, "eu-west-1").with_attribute("ingress", ingress)
+    }
+
+    async fn setup(resources: Vec<DiscoveredResource>) -> Fixture {
+        let tenant = Uuid::new_v4();
+        let at = Utc.with_ymd_and_hms(2025, 7, 14, 9, 0, 0).unwrap();
+        let inventory = Arc::new(InventoryService::new(Arc::new(InMemoryInventoryStore::new())));
+        let compliance = Arc::new(
+            ComplianceService::new(Arc::new(InMemoryComplianceStore::new()))
+                .with_resources(inventory.clone())
+                .with_required_tags(Vec::<String>::new()),
+        );
+        let run = DiscoveryRun {
+            id: Uuid::new_v4(),
+            tenant_id: tenant,
+            provider: "aws".into(),
+            account_id: "123456789012".into(),
+            resource_count: resources.len() as i64,
+            started_at: at,
+            completed_at: at,
+        };
+        inventory.ingest(&run, &resources).await.unwrap();
+        compliance.scan(tenant, at).await.unwrap();
+        let executor = Arc::new(FakeExecutor::default());
+        let rescanner = Arc::new(FakeRescanner::default());
+        let service = RemediationService::new(Arc::new(InMemoryRemediationStore::new()), compliance.clone())
+            .with_executor(executor.clone())
+            .with_inventory(inventory.clone())
+            .with_rescanner(rescanner.clone());
+        Fixture { tenant, inventory, compliance, executor, rescanner, service, at }
+    }
+
+    fn close_ssh(auto_remediate: bool, max_concurrent: u32) -> NewRemediationMapping {
+        NewRemediationMapping {
+            rule_key: "builtin.open-ssh".into(),
+            template_id: "aws-revoke-ingress".into(),
+            parameters: BTreeMap::from([
+                ("group_id".to_string(), "resource.resource_key.endsWith('sg-web') ? 'sg-web' : resource.tags['group']".to_string()),
+                ("region".to_string(), "resource.region".to_string()),
+                ("cidrs".to_string(), "resource.attributes.ingress.map(r, r.cidr)".to_string()),
+                ("finding".to_string(), "finding.id".to_string()),
+            ]),
+            auto_remediate,
+            max_concurrent,
+        }
+    }
+
+    async fn open_finding(fixture: &Fixture, resource_key: &str) -> Finding {
+        let open = fixture.compliance.findings(fixture.tenant, &FindingFilter::default(), 100).await.unwrap();
+        open.into_iter().find(|f| f.resource_key == resource_key).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_bindings_exclusion_and_concurrency_cap() {
+        let fixture = setup(vec![
+            group("sg-web", "0.0.0.0/0"),
+            group("sg-batch", "0.0.0.0/0").with_tags([("group".to_string(), "sg-batch".to_string())]),
+            group("sg-bastion", "0.0.0.0/0").with_tags([(DEFAULT_EXCLUSION_TAG.to_string(), "true".to_string())]),
+            group("sg-office", "203.0.113.0/24"),
+        ])
+        .await;
+        let (tenant, operator) = (fixture.tenant, Uuid::new_v4());
+        let mut broken = close_ssh(false, 1);
+        broken.parameters.insert("oops".into(), "resource.".into());
+        assert!(matches!(fixture.service.create_mapping(tenant, broken, operator, fixture.at).await, Err(AppError::Validation(_))));
+        let mapping = fixture.service.create_mapping(tenant, close_ssh(false, 1), operator, fixture.at).await.unwrap();
+        assert!(fixture.service.create_mapping(tenant, close_ssh(false, 1), operator, fixture.at).await.is_err());
+
+        let web = open_finding(&fixture, "ec2:security-group/sg-web").await;
+        let run = fixture.service.remediate(tenant, web.id, operator, Some("INC-42".into()), fixture.at).await.unwrap();
+        let started = fixture.executor.started.lock().unwrap().clone();
+        assert_eq!(started.len(), 1);
+        assert_eq!(started[0].0, "aws-revoke-ingress");
+        assert_eq!(started[0].2, run.id.to_string());
+        assert_eq!(started[0].1, run.parameters);
+        assert_eq!(run.parameters, BTreeMap::from([
+            ("cidrs".to_string(), json!(["0.0.0.0/0"])),
+            ("finding".to_string(), json!(web.id)),
+            ("group_id".to_string(), json!("sg-web")),
+            ("region".to_string(), json!("eu-west-1")),
+        ]));
+        let web = fixture.compliance.finding(tenant, web.id).await.unwrap();
+        assert_eq!((web.status, web.remediation_id), (FindingStatus::Remediating, Some(run.id)));
+        assert!(matches!(
+            fixture.service.remediate(tenant, web.id, operator, None, fixture.at).await,
+            Err(AppError::QuotaExceeded { .. })
+        ));
+
+        let bastion = open_finding(&fixture, "ec2:security-group/sg-bastion").await;
+        let batch = open_finding(&fixture, "ec2:security-group/sg-batch").await;
+        fixture.service.update_mapping(tenant, mapping.id, MappingUpdate { auto_remediate: Some(true), max_concurrent: Some(5) }).await.unwrap();
+        assert!(matches!(
+            fixture.service.remediate(tenant, bastion.id, operator, None, fixture.at).await,
+            Err(AppError::Forbidden(_))
+        ));
+
+        // The cap counts the manual run; the excluded group is never picked up
+        fixture.service.update_mapping(tenant, mapping.id, MappingUpdate { max_concurrent: Some(2), ..Default::default() }).await.unwrap();
+        assert_eq!(fixture.service.auto_remediate(fixture.at).await.unwrap(), 1);
+        assert_eq!(fixture.service.auto_remediate(fixture.at).await.unwrap(), 0);
+        let batch = fixture.service.remediations(tenant, Some(batch.id), 10).await.unwrap();
+        assert_eq!(batch[0].parameters["group_id"], json!("sg-batch"));
+        assert_eq!(batch[0].trigger, RemediationTrigger::Auto { mapping_id: mapping.id });
+        let bastion = fixture.compliance.finding(tenant, bastion.id).await.unwrap();
+        assert_eq!(bastion.status, FindingStatus::Open);
+    }
+
+    #[tokio::test]
+    async fn test_rescan_verifies_remediation() {
+        let fixture = setup(vec![group("sg-web", "0.0.0.0/0")]).await;
+        let (tenant, operator) = (fixture.tenant, Uuid::new_v4());
+        fixture.service.create_mapping(tenant, close_ssh(false, 1), operator, fixture.at).await.unwrap();
+        let finding = open_finding(&fixture, "ec2:security-group/sg-web").await;
+        let inventoried = fixture.inventory.resource(tenant, "aws", "123456789012", &finding.resource_key, "eu-west-1").await;
+        let still_open = inventoried.unwrap().unwrap();
+
+        // The workflow reports success but the group is still open
+        let first = fixture.service.remediate(tenant, finding.id, operator, None, fixture.at).await.unwrap();
+        assert_eq!(fixture.service.poll(fixture.at).await.unwrap(), 0);
+        fixture.rescanner.0.lock().unwrap().insert(finding.resource_key.clone(), still_open.clone());
+        fixture.executor.finish_all(WorkflowOutcome::Succeeded);
+        assert_eq!(fixture.service.poll(fixture.at).await.unwrap(), 1);
+        let detail = fixture.service.remediation(tenant, first.id).await.unwrap();
+        assert_eq!(detail.remediation.status, RemediationStatus::Unverified);
+        assert_eq!(fixture.compliance.finding(tenant, finding.id).await.unwrap().status, FindingStatus::Open);
+
+        // Retried, and this time the rescan no longer sees the violation
+        let later = fixture.at + Duration::minutes(10);
+        let second = fixture.service.remediate(tenant, finding.id, operator, None, later).await.unwrap();
+        let mut fixed = still_open;
+        fixed.attributes.insert("ingress".into(), json!([{"cidr": "10.0.0.0/8", "from_port": "22", "to_port": "22"}]));
+        fixture.rescanner.0.lock().unwrap().insert(finding.resource_key.clone(), fixed);
+        fixture.executor.finish_all(WorkflowOutcome::Succeeded);
+        assert_eq!(fixture.service.poll(later).await.unwrap(), 1);
+
+        let detail = fixture.service.remediation(tenant, second.id).await.unwrap();
+        assert_eq!(detail.remediation.status, RemediationStatus::Verified);
+        let kinds: Vec<&str> = detail.events.iter().map(|e| e.kind.as_str()).collect();
+        assert_eq!(kinds, vec!["triggered", "workflow_started", "workflow_succeeded", "verified"]);
+        let finding = fixture.compliance.finding(tenant, finding.id).await.unwrap();
+        assert_eq!((finding.status, finding.resolved_at, finding.remediation_id), (FindingStatus::Resolved, Some(later), Some(second.id)));
+    }
+}
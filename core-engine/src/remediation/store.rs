@@ -0,0 +1,469 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use sqlx::types::Json;
+use sqlx::PgPool; // CockroachDB uses PostgreSQL protocol
+use uuid::Uuid;
+
+use super::{Remediation, RemediationEvent, RemediationMapping, RemediationStatus, RemediationStore, RemediationTrigger};
+use crate::error::{AppError, AppResult};
+
+const MAPPING_COLUMNS: &str =
+    "id, tenant_id, rule_key, template_id, parameters, auto_remediate, max_concurrent, created_by, created_at";
+
+const REMEDIATION_COLUMNS: &str = "id, tenant_id, finding_id, mapping_id, rule_key, template_id, parameters, \
+    trigger, run_id, status, detail, started_at, completed_at";
+
+#[derive(sqlx::FromRow)]
+struct MappingRow {
+    id: Uuid,
+    tenant_id: Uuid,
+    rule_key: String,
+    template_id: String,
+    parameters: Json<BTreeMap<String, String>>,
+    auto_remediate: bool,
+    max_concurrent: i64,
+    created_by: Uuid,
+    created_at: DateTime<Utc>,
+}
+
+impl From<MappingRow> for RemediationMapping {
+    fn from(row: MappingRow) -> Self {
+        Self {
+            id: row.id,
+            tenant_id: row.tenant_id,
+            rule_key: row.rule_key,
+            template_id: row.template_id,
+            parameters: row.parameters.0,
+            auto_remediate: row.auto_remediate,
+            max_concurrent: row.max_concurrent.clamp(0, u32::MAX as i64) as u32,
+            created_by: row.created_by,
+            created_at: row.created_at,
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct RemediationRow {
+    id: Uuid,
+    tenant_id: Uuid,
+    finding_id: Uuid,
+    mapping_id: Uuid,
+    rule_key: String,
+    template_id: String,
+    parameters: Json<BTreeMap<String, Value>>,
+    trigger: Json<RemediationTrigger>,
+    run_id: Option<String>,
+    status: String,
+    detail: Option<String>,
+    started_at: DateTime<Utc>,
+    completed_at: Option<DateTime<Utc>>,
+}
+
+impl TryFrom<RemediationRow> for Remediation {
+    type Error = AppError;
+
+    fn try_from(row: RemediationRow) -> AppResult<Self> {
+        Ok(Self {
+            id: row.id,
+            tenant_id: row.tenant_id,
+            finding_id: row.finding_id,
+            mapping_id: row.mapping_id,
+            rule_key: row.rule_key,
+            template_id: row.template_id,
+            parameters: row.parameters.0,
+            trigger: row.trigger.0,
+            run_id: row.run_id,
+            status: RemediationStatus::parse(&row.status)?,
+            detail: row.detail,
+            started_at: row.started_at,
+            completed_at: row.completed_at,
+        })
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct EventRow {
+    remediation_id: Uuid,
+    tenant_id: Uuid,
+    kind: String,
+    detail: Json<Value>,
+    created_at: DateTime<Utc>,
+}
+
+impl From<EventRow> for RemediationEvent {
+    fn from(row: EventRow) -> Self {
+        Self {
+            remediation_id: row.remediation_id,
+            tenant_id: row.tenant_id,
+            kind: row.kind,
+            detail: row.detail.0,
+            created_at: row.created_at,
+        }
+    }
+}
+
+fn remediations_from(rows: Vec<RemediationRow>) -> AppResult<Vec<Remediation>> {
+    rows.into_iter().map(Remediation::try_from).collect()
+}
+
+pub struct PgRemediationStore {
+    pool: PgPool,
+}
+
+impl PgRemediationStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    async fn save_mapping(&self, mapping: &RemediationMapping, statement: &str) -> AppResult<()> {
+        sqlx::query(&format!("{} INTO remediation_mappings ({}) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)", statement, MAPPING_COLUMNS))
+            .bind(mapping.id)
+            .bind(mapping.tenant_id)
+            .bind(&mapping.rule_key)
+            .bind(&mapping.template_id)
+            .bind(Json(&mapping.parameters))
+            .bind(mapping.auto_remediate)
+            .bind(mapping.max_concurrent as i64)
+            .bind(mapping.created_by)
+            .bind(mapping.created_at)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| match e {
+                sqlx::Error::Database(ref db) if db.is_unique_violation() => AppError::Conflict {
+                    message: format!("Rule '{}' already has a remediation mapping", mapping.rule_key),
+                    current: None,
+                },
+                e => AppError::Database(e),
+            })?;
+
+        Ok(())
+    }
+
+    async fn save_remediation(&self, remediation: &Remediation, statement: &str) -> AppResult<()> {
+        sqlx::query(&format!(
+            "{} INTO remediations ({}) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)",
+            statement, REMEDIATION_COLUMNS
+        ))
+        .bind(remediation.id)
+        .bind(remediation.tenant_id)
+        .bind(remediation.finding_id)
+        .bind(remediation.mapping_id)
+        .bind(&remediation.rule_key)
+        .bind(&remediation.template_id)
+        .bind(Json(&remediation.parameters))
+        .bind(Json(&remediation.trigger))
+        .bind(&remediation.run_id)
+        .bind(remediation.status.as_str())
+        .bind(&remediation.detail)
+        .bind(remediation.started_at)
+        .bind(remediation.completed_at)
+        .execute(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl RemediationStore for PgRemediationStore {
+    async fn create_mapping(&self, mapping: &RemediationMapping) -> AppResult<()> {
+        self.save_mapping(mapping, "INSERT").await
+    }
+
+    async fn update_mapping(&self, mapping: &RemediationMapping) -> AppResult<()> {
+        self.save_mapping(mapping, "UPSERT").await
+    }
+
+    async fn mappings(&self, tenant_id: Uuid) -> AppResult<Vec<RemediationMapping>> {
+        let rows = sqlx::query_as::<_, MappingRow>(&format!(
+            "SELECT {} FROM remediation_mappings WHERE tenant_id = $1 ORDER BY rule_key",
+            MAPPING_COLUMNS
+        ))
+        .bind(tenant_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(rows.into_iter().map(RemediationMapping::from).collect())
+    }
+
+    async fn mapping(&self, tenant_id: Uuid, id: Uuid) -> AppResult<Option<RemediationMapping>> {
+        let row = sqlx::query_as::<_, MappingRow>(&format!(
+            "SELECT {} FROM remediation_mappings WHERE tenant_id = $1 AND id = $2",
+            MAPPING_COLUMNS
+        ))
+        .bind(tenant_id)
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(row.map(RemediationMapping::from))
+    }
+
+    async fn mapping_for_rule(&self, tenant_id: Uuid, rule_key: &str) -> AppResult<Option<RemediationMapping>> {
+        let row = sqlx::query_as::<_, MappingRow>(&format!(
+            "SELECT {} FROM remediation_mappings WHERE tenant_id = $1 AND rule_key = $2",
+            MAPPING_COLUMNS
+        ))
+        .bind(tenant_id)
+        .bind(rule_key)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(row.map(RemediationMapping::from))
+    }
+
+    async fn delete_mapping(&self, tenant_id: Uuid, id: Uuid) -> AppResult<bool> {
+        let result = sqlx::query("DELETE FROM remediation_mappings WHERE tenant_id = $1 AND id = $2")
+            .bind(tenant_id)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn auto_mappings(&self) -> AppResult<Vec<RemediationMapping>> {
+        let rows = sqlx::query_as::<_, MappingRow>(&format!(
+            "SELECT {} FROM remediation_mappings WHERE auto_remediate",
+            MAPPING_COLUMNS
+        ))
+        .fetch_all(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(rows.into_iter().map(RemediationMapping::from).collect())
+    }
+
+    async fn create_remediation(&self, remediation: &Remediation) -> AppResult<()> {
+        self.save_remediation(remediation, "INSERT").await
+    }
+
+    async fn update_remediation(&self, remediation: &Remediation) -> AppResult<()> {
+        self.save_remediation(remediation, "UPSERT").await
+    }
+
+    async fn remediation(&self, tenant_id: Uuid, id: Uuid) -> AppResult<Option<Remediation>> {
+        let row = sqlx::query_as::<_, RemediationRow>(&format!(
+            "SELECT {} FROM remediations WHERE tenant_id = $1 AND id = $2",
+            REMEDIATION_COLUMNS
+        ))
+        .bind(tenant_id)
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        row.map(Remediation::try_from).transpose()
+    }
+
+    async fn remediations(&self, tenant_id: Uuid, finding_id: Option<Uuid>, limit: i64) -> AppResult<Vec<Remediation>> {
+        let rows = sqlx::query_as::<_, RemediationRow>(&format!(
+            "SELECT {} FROM remediations WHERE tenant_id = $1 AND ($2::UUID IS NULL OR finding_id = $2) \
+            ORDER BY started_at DESC, id LIMIT $3",
+            REMEDIATION_COLUMNS
+        ))
+        .bind(tenant_id)
+        .bind(finding_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        remediations_from(rows)
+    }
+
+    async fn running(&self) -> AppResult<Vec<Remediation>> {
+        let rows = sqlx::query_as::<_, RemediationRow>(&format!(
+            "SELECT {} FROM remediations WHERE status = 'running' ORDER BY started_at",
+            REMEDIATION_COLUMNS
+        ))
+        .fetch_all(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        remediations_from(rows)
+    }
+
+    async fn running_count(&self, mapping_id: Uuid) -> AppResult<u32> {
+        let count: i64 =
+            sqlx::query_scalar("SELECT count(*) FROM remediations WHERE mapping_id = $1 AND status = 'running'")
+                .bind(mapping_id)
+                .fetch_one(&self.pool)
+                .await
+                .map_err(AppError::Database)?;
+
+        Ok(count as u32)
+    }
+
+    async fn record_event(&self, event: &RemediationEvent) -> AppResult<()> {
+        sqlx::query(
+            r#"INSERT INTO remediation_events (remediation_id, tenant_id, kind, detail, created_at)
+            VALUES ($1, $2, $3, $4, $5)"#
+        )
+        .bind(event.remediation_id)
+        .bind(event.tenant_id)
+        .bind(&event.kind)
+        .bind(Json(&event.detail))
+        .bind(event.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(())
+    }
+
+    async fn events(&self, tenant_id: Uuid, remediation_id: Uuid) -> AppResult<Vec<RemediationEvent>> {
+        let rows = sqlx::query_as::<_, EventRow>(
+            r#"SELECT remediation_id, tenant_id, kind, detail, created_at FROM remediation_events
+            WHERE tenant_id = $1 AND remediation_id = $2 ORDER BY created_at, seq"#
+        )
+        .bind(tenant_id)
+        .bind(remediation_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(rows.into_iter().map(RemediationEvent::from).collect())
+    }
+}
+
+#[derive(Default)]
+struct RemediationState {
+    mappings: Vec<RemediationMapping>,
+    remediations: Vec<Remediation>,
+    events: Vec<RemediationEvent>,
+}
+
+#[derive(Default)]
+pub struct InMemoryRemediationStore {
+    state: Mutex<RemediationState>,
+}
+
+impl InMemoryRemediationStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl RemediationStore for InMemoryRemediationStore {
+    async fn create_mapping(&self, mapping: &RemediationMapping) -> AppResult<()> {
+        let mut state = self.state.lock().unwrap();
+        if state.mappings.iter().any(|m| m.tenant_id == mapping.tenant_id && m.rule_key == mapping.rule_key) {
+            return Err(AppError::Conflict {
+                message: format!("Rule '{}' already has a remediation mapping", mapping.rule_key),
+                current: None,
+            });
+        }
+        state.mappings.push(mapping.clone());
+        Ok(())
+    }
+
+    async fn update_mapping(&self, mapping: &RemediationMapping) -> AppResult<()> {
+        let mut state = self.state.lock().unwrap();
+        state.mappings.retain(|m| m.id != mapping.id);
+        state.mappings.push(mapping.clone());
+        Ok(())
+    }
+
+    async fn mappings(&self, tenant_id: Uuid) -> AppResult<Vec<RemediationMapping>> {
+        let state = self.state.lock().unwrap();
+        let mut mappings: Vec<RemediationMapping> =
+            state.mappings.iter().filter(|m| m.tenant_id == tenant_id).cloned().collect();
+        mappings.sort_by(|a, b| a.rule_key.cmp(&b.rule_key));
+        Ok(mappings)
+    }
+
+    async fn mapping(&self, tenant_id: Uuid, id: Uuid) -> AppResult<Option<RemediationMapping>> {
+        let state = self.state.lock().unwrap();
+        Ok(state.mappings.iter().find(|m| m.tenant_id == tenant_id && m.id == id).cloned())
+    }
+
+    async fn mapping_for_rule(&self, tenant_id: Uuid, rule_key: &str) -> AppResult<Option<RemediationMapping>> {
+        let state = self.state.lock().unwrap();
+        Ok(state.mappings.iter().find(|m| m.tenant_id == tenant_id && m.rule_key == rule_key).cloned())
+    }
+
+    async fn delete_mapping(&self, tenant_id: Uuid, id: Uuid) -> AppResult<bool> {
+        let mut state = self.state.lock().unwrap();
+        let before = state.mappings.len();
+        state.mappings.retain(|m| !(m.tenant_id == tenant_id && m.id == id));
+        Ok(state.mappings.len() < before)
+    }
+
+    async fn auto_mappings(&self) -> AppResult<Vec<RemediationMapping>> {
+        let state = self.state.lock().unwrap();
+        Ok(state.mappings.iter().filter(|m| m.auto_remediate).cloned().collect())
+    }
+
+    async fn create_remediation(&self, remediation: &Remediation) -> AppResult<()> {
+        self.state.lock().unwrap().remediations.push(remediation.clone());
+        Ok(())
+    }
+
+    async fn update_remediation(&self, remediation: &Remediation) -> AppResult<()> {
+        let mut state = self.state.lock().unwrap();
+        state.remediations.retain(|r| r.id != remediation.id);
+        state.remediations.push(remediation.clone());
+        Ok(())
+    }
+
+    async fn remediation(&self, tenant_id: Uuid, id: Uuid) -> AppResult<Option<Remediation>> {
+        let state = self.state.lock().unwrap();
+        Ok(state.remediations.iter().find(|r| r.tenant_id == tenant_id && r.id == id).cloned())
+    }
+
+    async fn remediations(&self, tenant_id: Uuid, finding_id: Option<Uuid>, limit: i64) -> AppResult<Vec<Remediation>> {
+        let state = self.state.lock().unwrap();
+        let mut remediations: Vec<Remediation> = state
+            .remediations
+            .iter()
+            .filter(|r| r.tenant_id == tenant_id && finding_id.is_none_or(|f| f == r.finding_id))
+            .cloned()
+            .collect();
+        remediations.sort_by(|a, b| b.started_at.cmp(&a.started_at).then_with(|| a.id.cmp(&b.id)));
+        remediations.truncate(limit.max(0) as usize);
+        Ok(remediations)
+    }
+
+    async fn running(&self) -> AppResult<Vec<Remediation>> {
+        let state = self.state.lock().unwrap();
+        let mut running: Vec<Remediation> =
+            state.remediations.iter().filter(|r| r.status == RemediationStatus::Running).cloned().collect();
+        running.sort_by_key(|r| r.started_at);
+        Ok(running)
+    }
+
+    async fn running_count(&self, mapping_id: Uuid) -> AppResult<u32> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .remediations
+            .iter()
+            .filter(|r| r.mapping_id == mapping_id && r.status == RemediationStatus::Running)
+            .count() as u32)
+    }
+
+    async fn record_event(&self, event: &RemediationEvent) -> AppResult<()> {
+        self.state.lock().unwrap().events.push(event.clone());
+        Ok(())
+    }
+
+    async fn events(&self, tenant_id: Uuid, remediation_id: Uuid) -> AppResult<Vec<RemediationEvent>> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .events
+            .iter()
+            .filter(|e| e.tenant_id == tenant_id && e.remediation_id == remediation_id)
+            .cloned()
+            .collect())
+    }
+}
@@ -0,0 +1,649 @@
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration as StdDuration;
+
+use axum::async_trait;
+use chrono::{DateTime, Datelike, Duration, TimeZone, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+use sirsi_compute_manager::optimization::Recommendation;
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::error::{AppError, AppResult};
+use crate::metering::{MeteringService, UsageEvent};
+
+pub mod render;
+pub mod store;
+
+pub use render::{render_csv, render_html};
+pub use store::{InMemoryReportStore, PgReportStore};
+
+const DEFAULT_TOP_RESOURCES: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportSection {
+    CostTrend,
+    TopResources,
+    Recommendations,
+    SloSummary,
+    Usage,
+}
+
+impl ReportSection {
+    pub const ALL: &'static [ReportSection] = &[
+        ReportSection::CostTrend,
+        ReportSection::TopResources,
+        ReportSection::Recommendations,
+        ReportSection::SloSummary,
+        ReportSection::Usage,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReportSection::CostTrend => "cost_trend",
+            ReportSection::TopResources => "top_resources",
+            ReportSection::Recommendations => "recommendations",
+            ReportSection::SloSummary => "slo_summary",
+            ReportSection::Usage => "usage",
+        }
+    }
+}
+
+// Runs once a week at the top of `hour`, UTC
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WeeklySchedule {
+    pub day: Weekday,
+    pub hour: u32,
+}
+
+impl WeeklySchedule {
+    // The latest scheduled instant at or before `now`
+    pub fn previous(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        let days_back = (now.weekday().num_days_from_monday() + 7 - self.day.num_days_from_monday()) % 7;
+        let day = now.date_naive() - Duration::days(days_back as i64);
+        let slot = Utc.from_utc_datetime(&day.and_hms_opt(self.hour, 0, 0).unwrap_or_default());
+        if slot > now {
+            slot - Duration::weeks(1)
+        } else {
+            slot
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "channel", rename_all = "snake_case")]
+pub enum Recipient {
+    Email { address: String },
+    Slack { channel_id: String },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReportDefinition {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    // The whole tenant when unset
+    pub project_id: Option<Uuid>,
+    pub name: String,
+    pub sections: Vec<ReportSection>,
+    pub schedule: WeeklySchedule,
+    pub recipients: Vec<Recipient>,
+    pub created_at: DateTime<Utc>,
+    // The last schedule slot a run was started for
+    pub last_scheduled_for: Option<DateTime<Utc>>,
+}
+
+impl ReportDefinition {
+    pub fn includes(&self, section: ReportSection) -> bool {
+        self.sections.contains(&section)
+    }
+
+    // The slot to run for, if one has passed since the last scheduled run
+    pub fn due_slot(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let slot = self.schedule.previous(now);
+        (slot > self.last_scheduled_for.unwrap_or(self.created_at)).then_some(slot)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewReport {
+    pub name: String,
+    pub project_id: Option<Uuid>,
+    // Every section when omitted
+    #[serde(default)]
+    pub sections: Vec<ReportSection>,
+    pub schedule: WeeklySchedule,
+    pub recipients: Vec<Recipient>,
+}
+
+// Half-open `[start, end)` range a report covers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ReportPeriod {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+impl ReportPeriod {
+    // The seven whole UTC days before the day `at` falls on
+    pub fn week_ending(at: DateTime<Utc>) -> Self {
+        let end = Utc.from_utc_datetime(&at.date_naive().and_hms_opt(0, 0, 0).unwrap_or_default());
+        Self { start: end - Duration::weeks(1), end }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CostPoint {
+    pub day: chrono::NaiveDate,
+    pub amount: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResourceCost {
+    pub resource_id: String,
+    pub name: Option<String>,
+    pub resource_type: String,
+    pub amount: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SloSummary {
+    pub name: String,
+    // Percentages, e.g. 99.9
+    pub objective: f64,
+    pub attained: f64,
+    // Fraction of the period's error budget still unspent
+    pub error_budget_remaining: f64,
+}
+
+impl SloSummary {
+    pub fn met(&self) -> bool {
+        self.attained >= self.objective
+    }
+}
+
+// Everything a report can show, for one scope and period
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReportData {
+    pub currency: String,
+    pub cost_trend: Vec<CostPoint>,
+    // Total of the period before, for the week-over-week change
+    pub previous_total: Option<f64>,
+    pub top_resources: Vec<ResourceCost>,
+    pub recommendations: Vec<Recommendation>,
+    pub slos: Vec<SloSummary>,
+    #[serde(default)]
+    pub usage: BTreeMap<UsageEvent, i64>,
+}
+
+// Cost, optimization and SLO figures for a tenant or one of its projects
+#[async_trait]
+pub trait ReportDataSource: Send + Sync {
+    async fn collect(&self, tenant_id: Uuid, project_id: Option<Uuid>, period: &ReportPeriod) -> AppResult<ReportData>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReportArtifact {
+    pub name: String,
+    pub content_type: String,
+    #[serde(skip)]
+    pub content: Vec<u8>,
+}
+
+impl ReportArtifact {
+    pub fn new(name: impl Into<String>, content_type: impl Into<String>, content: impl Into<Vec<u8>>) -> Self {
+        Self { name: name.into(), content_type: content_type.into(), content: content.into() }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ReportMessage {
+    pub subject: String,
+    pub html: String,
+    pub attachments: Vec<ReportArtifact>,
+}
+
+// Hands a rendered report to the email or Slack channel a recipient names
+#[async_trait]
+pub trait ReportNotifier: Send + Sync {
+    async fn deliver(&self, recipient: &Recipient, message: &ReportMessage) -> AppResult<()>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "VARCHAR", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum RunTrigger {
+    Scheduled,
+    Manual,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "VARCHAR", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ReportRunStatus {
+    Completed,
+    Failed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Delivery {
+    pub recipient: Recipient,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArtifactInfo {
+    pub name: String,
+    pub content_type: String,
+    pub size: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReportRun {
+    pub id: Uuid,
+    pub report_id: Uuid,
+    pub tenant_id: Uuid,
+    pub trigger: RunTrigger,
+    pub period: ReportPeriod,
+    pub status: ReportRunStatus,
+    pub error: Option<String>,
+    pub deliveries: Vec<Delivery>,
+    pub artifacts: Vec<ArtifactInfo>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[async_trait]
+pub trait ReportStore: Send + Sync {
+    async fn create_report(&self, report: &ReportDefinition) -> AppResult<()>;
+    async fn get_report(&self, tenant_id: Uuid, id: Uuid) -> AppResult<Option<ReportDefinition>>;
+    // All tenants when `tenant_id` is `None`
+    async fn list_reports(&self, tenant_id: Option<Uuid>) -> AppResult<Vec<ReportDefinition>>;
+    async fn delete_report(&self, tenant_id: Uuid, id: Uuid) -> AppResult<bool>;
+    // Moves `last_scheduled_for` from `previous` to `slot`; false when another scheduler got there first
+    async fn claim_slot(&self, id: Uuid, previous: Option<DateTime<Utc>>, slot: DateTime<Utc>) -> AppResult<bool>;
+    async fn create_run(&self, run: &ReportRun, artifacts: &[ReportArtifact]) -> AppResult<()>;
+    // Newest first
+    async fn list_runs(&self, tenant_id: Uuid, report_id: Uuid, limit: i64) -> AppResult<Vec<ReportRun>>;
+    async fn get_run(&self, tenant_id: Uuid, run_id: Uuid) -> AppResult<Option<ReportRun>>;
+    async fn get_artifact(&self, tenant_id: Uuid, run_id: Uuid, name: &str) -> AppResult<Option<ReportArtifact>>;
+}
+
+pub struct ReportService {
+    store: Arc<dyn ReportStore>,
+    source: Option<Arc<dyn ReportDataSource>>,
+    notifier: Option<Arc<dyn ReportNotifier>>,
+    metering: Option<Arc<MeteringService>>,
+    top_resources: usize,
+}
+
+impl ReportService {
+    pub fn new(store: Arc<dyn ReportStore>) -> Self {
+        Self { store, source: None, notifier: None, metering: None, top_resources: DEFAULT_TOP_RESOURCES }
+    }
+
+    // Runs fail with a configuration error until a source is attached
+    pub fn with_source(mut self, source: Arc<dyn ReportDataSource>) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    // Without a notifier runs are still stored, with every delivery marked failed
+    pub fn with_notifier(mut self, notifier: Arc<dyn ReportNotifier>) -> Self {
+        self.notifier = Some(notifier);
+        self
+    }
+
+    // Fills the usage section; metering is per tenant, so project reports show tenant totals
+    pub fn with_metering(mut self, metering: Arc<MeteringService>) -> Self {
+        self.metering = Some(metering);
+        self
+    }
+
+    pub fn with_top_resources(mut self, limit: usize) -> Self {
+        self.top_resources = limit;
+        self
+    }
+
+    pub async fn create(&self, tenant_id: Uuid, report: NewReport, now: DateTime<Utc>) -> AppResult<ReportDefinition> {
+        if report.name.trim().is_empty() {
+            return Err(AppError::Validation("Report name is required".into()));
+        }
+        if report.schedule.hour > 23 {
+            return Err(AppError::Validation("Schedule hour must be between 0 and 23".into()));
+        }
+        if report.recipients.is_empty() {
+            return Err(AppError::Validation("A report needs at least one recipient".into()));
+        }
+        let blank = report.recipients.iter().any(|r| match r {
+            Recipient::Email { address } => !address.contains('@'),
+            Recipient::Slack { channel_id } => channel_id.trim().is_empty(),
+        });
+        if blank {
+            return Err(AppError::Validation("Recipients need an email address or Slack channel id".into()));
+        }
+        let mut sections = if report.sections.is_empty() { ReportSection::ALL.to_vec() } else { report.sections };
+        let mut seen = HashSet::new();
+        sections.retain(|s| seen.insert(*s));
+        let definition = ReportDefinition {
+            id: Uuid::new_v4(),
+            tenant_id,
+            project_id: report.project_id,
+            name: report.name,
+            sections,
+            schedule: report.schedule,
+            recipients: report.recipients,
+            created_at: now,
+            last_scheduled_for: None,
+        };
+        self.store.create_report(&definition).await?;
+        Ok(definition)
+    }
+
+    pub async fn reports(&self, tenant_id: Uuid) -> AppResult<Vec<ReportDefinition>> {
+        self.store.list_reports(Some(tenant_id)).await
+    }
+
+    pub async fn report(&self, tenant_id: Uuid, id: Uuid) -> AppResult<ReportDefinition> {
+        self.store
+            .get_report(tenant_id, id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Report not found".into()))
+    }
+
+    pub async fn delete(&self, tenant_id: Uuid, id: Uuid) -> AppResult<()> {
+        if !self.store.delete_report(tenant_id, id).await? {
+            return Err(AppError::NotFound("Report not found".into()));
+        }
+        Ok(())
+    }
+
+    // Covers the week before today and leaves the schedule untouched
+    pub async fn run_now(&self, tenant_id: Uuid, id: Uuid, now: DateTime<Utc>) -> AppResult<ReportRun> {
+        let report = self.report(tenant_id, id).await?;
+        self.execute(&report, RunTrigger::Manual, ReportPeriod::week_ending(now), now).await
+    }
+
+    // Runs every report whose slot has passed, each covering the week before its slot
+    pub async fn run_due(&self, now: DateTime<Utc>) -> AppResult<Vec<ReportRun>> {
+        if self.source.is_none() {
+            return Ok(Vec::new());
+        }
+        let mut runs = Vec::new();
+        for report in self.store.list_reports(None).await? {
+            let Some(slot) = report.due_slot(now) else {
+                continue;
+            };
+            if !self.store.claim_slot(report.id, report.last_scheduled_for, slot).await? {
+                continue;
+            }
+            match self.execute(&report, RunTrigger::Scheduled, ReportPeriod::week_ending(slot), now).await {
+                Ok(run) => runs.push(run),
+                Err(e) => error!("Scheduled report {} failed: {}", report.id, e),
+            }
+        }
+        Ok(runs)
+    }
+
+    pub fn spawn_scheduler(self: Arc<Self>, interval: StdDuration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                match self.run_due(Utc::now()).await {
+                    Ok(runs) if !runs.is_empty() => info!("Ran {} scheduled reports", runs.len()),
+                    Ok(_) => {}
+                    Err(e) => warn!("Failed to check for due reports: {}", e),
+                }
+            }
+        })
+    }
+
+    pub async fn runs(&self, tenant_id: Uuid, report_id: Uuid, limit: i64) -> AppResult<Vec<ReportRun>> {
+        self.report(tenant_id, report_id).await?;
+        self.store.list_runs(tenant_id, report_id, limit).await
+    }
+
+    pub async fn run(&self, tenant_id: Uuid, run_id: Uuid) -> AppResult<ReportRun> {
+        self.store
+            .get_run(tenant_id, run_id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Report run not found".into()))
+    }
+
+    pub async fn artifact(&self, tenant_id: Uuid, run_id: Uuid, name: &str) -> AppResult<ReportArtifact> {
+        self.store
+            .get_artifact(tenant_id, run_id, name)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Report artifact not found".into()))
+    }
+
+    // A failed collection is stored as a failed run rather than returned as an error
+    async fn execute(
+        &self,
+        report: &ReportDefinition,
+        trigger: RunTrigger,
+        period: ReportPeriod,
+        now: DateTime<Utc>,
+    ) -> AppResult<ReportRun> {
+        let source = self
+            .source
+            .as_ref()
+            .ok_or_else(|| AppError::Configuration("No report data source configured".into()))?;
+        let mut run = ReportRun {
+            id: Uuid::new_v4(),
+            report_id: report.id,
+            tenant_id: report.tenant_id,
+            trigger,
+            period,
+            status: ReportRunStatus::Completed,
+            error: None,
+            deliveries: Vec::new(),
+            artifacts: Vec::new(),
+            created_at: now,
+        };
+        let artifacts = match self.collect(source.as_ref(), report, &period).await {
+            Ok(data) => {
+                let html = render_html(report, &period, &data);
+                let mut artifacts = vec![ReportArtifact::new("report.html", "text/html; charset=utf-8", html.clone())];
+                artifacts.extend(report.sections.iter().filter_map(|&section| render_csv(section, &data)));
+                let message = ReportMessage {
+                    subject: format!("{}: {} to {}", report.name, period.start.date_naive(), last_day(&period)),
+                    html,
+                    attachments: artifacts[1..].to_vec(),
+                };
+                run.deliveries = self.deliver(report, &message).await;
+                artifacts
+            }
+            Err(e) => {
+                warn!("Failed to collect data for report {}: {}", report.id, e);
+                run.status = ReportRunStatus::Failed;
+                run.error = Some(e.to_string());
+                Vec::new()
+            }
+        };
+        run.artifacts = artifacts
+            .iter()
+            .map(|a| ArtifactInfo { name: a.name.clone(), content_type: a.content_type.clone(), size: a.content.len() })
+            .collect();
+        self.store.create_run(&run, &artifacts).await?;
+        Ok(run)
+    }
+
+    async fn collect(&self, source: &dyn ReportDataSource, report: &ReportDefinition, period: &ReportPeriod) -> AppResult<ReportData> {
+        let mut data = source.collect(report.tenant_id, report.project_id, period).await?;
+        data.cost_trend.sort_by_key(|p| p.day);
+        data.top_resources.sort_by(|a, b| b.amount.total_cmp(&a.amount).then_with(|| a.resource_id.cmp(&b.resource_id)));
+        data.top_resources.truncate(self.top_resources);
+        data.recommendations
+            .sort_by(|a, b| b.impact.cost_savings.amount.total_cmp(&a.impact.cost_savings.amount));
+        if let (Some(metering), true) = (&self.metering, report.includes(ReportSection::Usage)) {
+            let usage = metering.usage(report.tenant_id, period.start.date_naive(), last_day(period)).await?;
+            data.usage = usage.totals;
+        }
+        Ok(data)
+    }
+
+    // One recipient failing does not stop delivery to the rest
+    async fn deliver(&self, report: &ReportDefinition, message: &ReportMessage) -> Vec<Delivery> {
+        let mut deliveries = Vec::with_capacity(report.recipients.len());
+        for recipient in &report.recipients {
+            let result = match &self.notifier {
+                Some(notifier) => notifier.deliver(recipient, message).await,
+                None => Err(AppError::Configuration("No report notifier configured".into())),
+            };
+            if let Err(e) = &result {
+                warn!("Failed to deliver report {} to {:?}: {}", report.id, recipient, e);
+            }
+            deliveries.push(Delivery { recipient: recipient.clone(), error: result.err().map(|e| e.to_string()) });
+        }
+        deliveries
+    }
+}
+
+// The period end is exclusive, so the last day covered is the one before it
+pub(crate) fn last_day(period: &ReportPeriod) -> chrono::NaiveDate {
+    (period.end - Duration::nanoseconds(1)).date_naive()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sirsi_compute_manager::optimization::{Cost, Impact, OptimizationStrategy, PerformanceImpact, Period, ReliabilityImpact};
+    use std::collections::HashMap;
+    use tokio::sync::Mutex;
+
+    struct FixtureSource;
+
+    #[async_trait]
+    impl ReportDataSource for FixtureSource {
+        async fn collect(&self, _tenant_id: Uuid, _project_id: Option<Uuid>, period: &ReportPeriod) -> AppResult<ReportData> {
+            Ok(fixture_data(period))
+        }
+    }
+
+    #[derive(Default)]
+    struct RecordingNotifier {
+        sent: Mutex<Vec<(Recipient, ReportMessage)>>,
+    }
+
+    #[async_trait]
+    impl ReportNotifier for RecordingNotifier {
+        async fn deliver(&self, recipient: &Recipient, message: &ReportMessage) -> AppResult<()> {
+            if let Recipient::Slack { channel_id } = recipient {
+                if channel_id == "C-ARCHIVED" {
+                    return Err(AppError::ExternalService("channel_not_found".into()));
+                }
+            }
+            self.sent.lock().await.push((recipient.clone(), message.clone()));
+            Ok(())
+        }
+    }
+
+    pub(super) fn fixture_data(period: &ReportPeriod) -> ReportData {
+        let day = period.start.date_naive();
+        let savings = |amount: f64| Cost {
+            amount,
+            currency: "USD".into(),
+            period: Period::Monthly,
+            components: HashMap::new(),
+        };
+        ReportData {
+            currency: "USD".into(),
+            cost_trend: (0..7).map(|i| CostPoint { day: day + Duration::days(i), amount: 100.0 + 10.0 * i as f64 }).collect(),
+            previous_total: Some(800.0),
+            top_resources: vec![
+                ResourceCost { resource_id: "i-0a1b".into(), name: Some("api <blue>".into()), resource_type: "ec2_instance".into(), amount: 412.5 },
+                ResourceCost { resource_id: "db-orders".into(), name: None, resource_type: "rds_instance".into(), amount: 288.0 },
+            ],
+            recommendations: vec![
+                Recommendation {
+                    strategy: OptimizationStrategy::ConvertToSpot { instance_ids: vec!["i-1".into(), "i-2".into()] },
+                    impact: Impact { cost_savings: savings(35.0), performance_impact: PerformanceImpact::Neutral, reliability_impact: ReliabilityImpact::Reduced },
+                    confidence: 0.6,
+                    reason: "Stateless workers, interruptions tolerated".into(),
+                },
+                Recommendation {
+                    strategy: OptimizationStrategy::ResizeInstance { instance_id: "i-0a1b".into(), new_type: "m5.large".into() },
+                    impact: Impact { cost_savings: savings(120.0), performance_impact: PerformanceImpact::Neutral, reliability_impact: ReliabilityImpact::Unchanged },
+                    confidence: 0.85,
+                    reason: "CPU below 15% for 14 days".into(),
+                },
+            ],
+            slos: vec![
+                SloSummary { name: "checkout availability".into(), objective: 99.9, attained: 99.95, error_budget_remaining: 0.5 },
+                SloSummary { name: "search latency p99 < 300ms".into(), objective: 99.0, attained: 98.2, error_budget_remaining: 0.0 },
+            ],
+            usage: BTreeMap::new(),
+        }
+    }
+
+    fn new_report(schedule: WeeklySchedule) -> NewReport {
+        NewReport {
+            name: "Weekly platform digest".into(),
+            project_id: None,
+            sections: Vec::new(),
+            schedule,
+            recipients: vec![
+                Recipient::Email { address: "owner@example.com".into() },
+                Recipient::Slack { channel_id: "C-ARCHIVED".into() },
+            ],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scheduled_runs_fire_once_per_slot() {
+        let notifier = Arc::new(RecordingNotifier::default());
+        let service = ReportService::new(Arc::new(InMemoryReportStore::new()))
+            .with_source(Arc::new(FixtureSource))
+            .with_notifier(notifier.clone());
+        let tenant = Uuid::new_v4();
+        // Wednesday 2025-07-02; the report goes out Mondays at 08:00 UTC
+        let created = Utc.with_ymd_and_hms(2025, 7, 2, 12, 0, 0).unwrap();
+        let report = service.create(tenant, new_report(WeeklySchedule { day: Weekday::Mon, hour: 8 }), created).await.unwrap();
+        assert_eq!(report.sections, ReportSection::ALL);
+
+        let before = Utc.with_ymd_and_hms(2025, 7, 7, 7, 59, 0).unwrap();
+        assert!(service.run_due(before).await.unwrap().is_empty());
+
+        let slot = Utc.with_ymd_and_hms(2025, 7, 7, 8, 0, 0).unwrap();
+        let runs = service.run_due(slot + Duration::minutes(3)).await.unwrap();
+        assert_eq!(runs.len(), 1);
+        let run = &runs[0];
+        assert_eq!(run.trigger, RunTrigger::Scheduled);
+        let midnight = Utc.with_ymd_and_hms(2025, 7, 7, 0, 0, 0).unwrap();
+        assert_eq!(run.period, ReportPeriod { start: midnight - Duration::weeks(1), end: midnight });
+        assert_eq!(run.status, ReportRunStatus::Completed);
+        assert_eq!(run.deliveries[0].error, None);
+        assert!(run.deliveries[1].error.as_deref().unwrap().contains("channel_not_found"));
+
+        // The same slot never runs twice, and a manual run leaves the schedule alone
+        assert!(service.run_due(slot + Duration::hours(5)).await.unwrap().is_empty());
+        let manual = service.run_now(tenant, report.id, slot + Duration::days(2)).await.unwrap();
+        assert_eq!(manual.trigger, RunTrigger::Manual);
+        let next = service.run_due(slot + Duration::weeks(1)).await.unwrap();
+        assert_eq!(next.len(), 1);
+        assert_eq!(next[0].period.end, midnight + Duration::weeks(1));
+
+        let sent = notifier.sent.lock().await;
+        assert_eq!(sent.len(), 3);
+        assert_eq!(sent[0].1.subject, "Weekly platform digest: 2025-06-30 to 2025-07-06");
+        let attachments: Vec<_> = sent[0].1.attachments.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(attachments, ["cost-trend.csv", "top-resources.csv", "recommendations.csv", "slo-summary.csv"]);
+
+        // Runs keep their artifacts for later download
+        let history = service.runs(tenant, report.id, 10).await.unwrap();
+        assert_eq!(history.iter().map(|r| r.id).collect::<Vec<_>>(), vec![next[0].id, manual.id, run.id]);
+        let html = service.artifact(tenant, run.id, "report.html").await.unwrap();
+        assert_eq!(html.content, sent[0].1.html.as_bytes());
+        assert!(service.artifact(Uuid::new_v4(), run.id, "report.html").await.is_err());
+    }
+
+    #[test]
+    fn test_weekly_schedule_previous_slot() {
+        let schedule = WeeklySchedule { day: Weekday::Fri, hour: 17 };
+        let friday = Utc.with_ymd_and_hms(2025, 7, 4, 17, 0, 0).unwrap();
+        assert_eq!(schedule.previous(friday), friday);
+        assert_eq!(schedule.previous(friday - Duration::seconds(1)), friday - Duration::weeks(1));
+        assert_eq!(schedule.previous(friday + Duration::days(6)), friday);
+    }
+}
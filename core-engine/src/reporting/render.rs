@@ -0,0 +1,304 @@
+use std::fmt::Write;
+
+use serde_json::{Map, Value};
+use sirsi_compute_manager::optimization::{Period, Recommendation};
+
+use super::{last_day, ReportArtifact, ReportData, ReportDefinition, ReportPeriod, ReportSection};
+use crate::api::export::{encode_header, encode_record, ExportFormat, ExportRecord};
+
+const COST_TREND_COLUMNS: &[&str] = &["day", "amount", "currency"];
+const TOP_RESOURCE_COLUMNS: &[&str] = &["resource_id", "name", "resource_type", "amount", "currency"];
+const RECOMMENDATION_COLUMNS: &[&str] = &["action", "reason", "estimated_savings", "currency", "period", "confidence"];
+const SLO_COLUMNS: &[&str] = &["name", "objective", "attained", "error_budget_remaining", "met"];
+const USAGE_COLUMNS: &[&str] = &["event", "count"];
+
+pub(crate) fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+pub(crate) fn money(amount: f64, currency: &str) -> String {
+    format!("{:.2} {}", amount, currency)
+}
+
+fn per_period(period: &Period) -> &'static str {
+    match period {
+        Period::Hourly => "/hour",
+        Period::Daily => "/day",
+        Period::Monthly => "/month",
+        Period::Total => "",
+    }
+}
+
+fn period_name(period: &Period) -> &'static str {
+    match period {
+        Period::Hourly => "hourly",
+        Period::Daily => "daily",
+        Period::Monthly => "monthly",
+        Period::Total => "total",
+    }
+}
+
+fn savings(recommendation: &Recommendation) -> String {
+    let cost = &recommendation.impact.cost_savings;
+    format!("{}{}", money(cost.amount, &cost.currency), per_period(&cost.period))
+}
+
+fn table(html: &mut String, headers: &[&str], rows: Vec<Vec<String>>) {
+    html.push_str("<table cellpadding=\"6\" style=\"border-collapse: collapse;\">\n<tr>");
+    for header in headers {
+        let _ = write!(html, "<th align=\"left\">{}</th>", header);
+    }
+    html.push_str("</tr>\n");
+    for row in rows {
+        html.push_str("<tr>");
+        for cell in row {
+            let _ = write!(html, "<td>{}</td>", cell);
+        }
+        html.push_str("</tr>\n");
+    }
+    html.push_str("</table>\n");
+}
+
+fn empty(html: &mut String, message: &str) {
+    let _ = writeln!(html, "<p>{}</p>", message);
+}
+
+fn cost_trend(html: &mut String, data: &ReportData) {
+    html.push_str("<h2>Cost trend</h2>\n");
+    if data.cost_trend.is_empty() {
+        return empty(html, "No cost data for this period.");
+    }
+    let total: f64 = data.cost_trend.iter().map(|p| p.amount).sum();
+    let change = match data.previous_total {
+        Some(previous) if previous > 0.0 => format!(" ({:+.1}% on the previous period)", (total - previous) / previous * 100.0),
+        _ => String::new(),
+    };
+    let _ = writeln!(html, "<p>Total: <strong>{}</strong>{}</p>", money(total, &data.currency), change);
+    let rows = data.cost_trend.iter().map(|p| vec![p.day.to_string(), money(p.amount, &data.currency)]).collect();
+    table(html, &["Day", "Cost"], rows);
+}
+
+fn top_resources(html: &mut String, data: &ReportData) {
+    html.push_str("<h2>Top resources by cost</h2>\n");
+    if data.top_resources.is_empty() {
+        return empty(html, "No resource costs for this period.");
+    }
+    let rows = data
+        .top_resources
+        .iter()
+        .map(|r| {
+            vec![
+                escape(r.name.as_deref().unwrap_or(&r.resource_id)),
+                escape(&r.resource_type),
+                money(r.amount, &data.currency),
+            ]
+        })
+        .collect();
+    table(html, &["Resource", "Type", "Cost"], rows);
+}
+
+fn recommendations(html: &mut String, data: &ReportData) {
+    html.push_str("<h2>Optimization recommendations</h2>\n");
+    if data.recommendations.is_empty() {
+        return empty(html, "No recommendations this period.");
+    }
+    let rows = data
+        .recommendations
+        .iter()
+        .map(|r| {
+            vec![
+                escape(&r.strategy.description()),
+                escape(&r.reason),
+                savings(r),
+                format!("{:.0}%", r.confidence * 100.0),
+            ]
+        })
+        .collect();
+    table(html, &["Action", "Reason", "Estimated savings", "Confidence"], rows);
+}
+
+fn slo_summary(html: &mut String, data: &ReportData) {
+    html.push_str("<h2>SLO summary</h2>\n");
+    if data.slos.is_empty() {
+        return empty(html, "No SLOs are tracked for this scope.");
+    }
+    let rows = data
+        .slos
+        .iter()
+        .map(|s| {
+            vec![
+                escape(&s.name),
+                format!("{:.2}%", s.objective),
+                format!("{:.2}%", s.attained),
+                format!("{:.0}%", s.error_budget_remaining * 100.0),
+                if s.met() { "Met" } else { "Breached" }.to_string(),
+            ]
+        })
+        .collect();
+    table(html, &["Objective", "Target", "Attained", "Error budget left", "Status"], rows);
+}
+
+fn usage(html: &mut String, data: &ReportData) {
+    html.push_str("<h2>Usage</h2>\n");
+    if data.usage.is_empty() {
+        return empty(html, "No metered usage this period.");
+    }
+    let rows = data.usage.iter().map(|(event, count)| vec![event.as_str().to_string(), count.to_string()]).collect();
+    table(html, &["Event", "Count"], rows);
+}
+
+// Inline styles only, since mail clients drop stylesheets
+pub fn render_html(report: &ReportDefinition, period: &ReportPeriod, data: &ReportData) -> String {
+    let mut html = String::new();
+    let title = escape(&report.name);
+    let _ = writeln!(html, "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>", title);
+    html.push_str("<body style=\"font-family: Helvetica, Arial, sans-serif; color: #1f2933;\">\n");
+    let _ = writeln!(html, "<h1>{}</h1>", title);
+    let _ = writeln!(html, "<p>{} to {} (UTC)</p>", period.start.date_naive(), last_day(period));
+    for section in &report.sections {
+        match section {
+            ReportSection::CostTrend => cost_trend(&mut html, data),
+            ReportSection::TopResources => top_resources(&mut html, data),
+            ReportSection::Recommendations => recommendations(&mut html, data),
+            ReportSection::SloSummary => slo_summary(&mut html, data),
+            ReportSection::Usage => usage(&mut html, data),
+        }
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+fn record(values: Vec<(&str, Value)>) -> ExportRecord {
+    values.into_iter().map(|(k, v)| (k.to_string(), v)).collect::<Map<_, _>>()
+}
+
+fn csv(columns: &[&str], records: Vec<ExportRecord>) -> String {
+    let mut csv = encode_header(ExportFormat::Csv, columns).unwrap_or_default();
+    for record in &records {
+        csv.push_str(&encode_record(ExportFormat::Csv, columns, record));
+    }
+    csv
+}
+
+// The section's rows as a CSV attachment, or `None` when it has nothing to tabulate
+pub fn render_csv(section: ReportSection, data: &ReportData) -> Option<ReportArtifact> {
+    let (name, content) = match section {
+        ReportSection::CostTrend if !data.cost_trend.is_empty() => {
+            let records = data
+                .cost_trend
+                .iter()
+                .map(|p| record(vec![("day", p.day.to_string().into()), ("amount", p.amount.into()), ("currency", data.currency.clone().into())]))
+                .collect();
+            ("cost-trend.csv", csv(COST_TREND_COLUMNS, records))
+        }
+        ReportSection::TopResources if !data.top_resources.is_empty() => {
+            let records = data
+                .top_resources
+                .iter()
+                .map(|r| {
+                    record(vec![
+                        ("resource_id", r.resource_id.clone().into()),
+                        ("name", r.name.clone().into()),
+                        ("resource_type", r.resource_type.clone().into()),
+                        ("amount", r.amount.into()),
+                        ("currency", data.currency.clone().into()),
+                    ])
+                })
+                .collect();
+            ("top-resources.csv", csv(TOP_RESOURCE_COLUMNS, records))
+        }
+        ReportSection::Recommendations if !data.recommendations.is_empty() => {
+            let records = data
+                .recommendations
+                .iter()
+                .map(|r| {
+                    let cost = &r.impact.cost_savings;
+                    record(vec![
+                        ("action", r.strategy.description().into()),
+                        ("reason", r.reason.clone().into()),
+                        ("estimated_savings", cost.amount.into()),
+                        ("currency", cost.currency.clone().into()),
+                        ("period", period_name(&cost.period).into()),
+                        ("confidence", r.confidence.into()),
+                    ])
+                })
+                .collect();
+            ("recommendations.csv", csv(RECOMMENDATION_COLUMNS, records))
+        }
+        ReportSection::SloSummary if !data.slos.is_empty() => {
+            let records = data
+                .slos
+                .iter()
+                .map(|s| {
+                    record(vec![
+                        ("name", s.name.clone().into()),
+                        ("objective", s.objective.into()),
+                        ("attained", s.attained.into()),
+                        ("error_budget_remaining", s.error_budget_remaining.into()),
+                        ("met", s.met().into()),
+                    ])
+                })
+                .collect();
+            ("slo-summary.csv", csv(SLO_COLUMNS, records))
+        }
+        ReportSection::Usage if !data.usage.is_empty() => {
+            let records = data
+                .usage
+                .iter()
+                .map(|(event, count)| record(vec![("event", event.as_str().into()), ("count", (*count).into())]))
+                .collect();
+            ("usage.csv", csv(USAGE_COLUMNS, records))
+        }
+        _ => return None,
+    };
+    Some(ReportArtifact::new(name, "text/csv; charset=utf-8", content))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reporting::{tests::fixture_data, WeeklySchedule};
+    use chrono::{TimeZone, Utc, Weekday};
+    use uuid::Uuid;
+
+    fn report() -> ReportDefinition {
+        ReportDefinition {
+            id: Uuid::nil(),
+            tenant_id: Uuid::nil(),
+            project_id: None,
+            name: "Platform & data weekly".into(),
+            sections: ReportSection::ALL.to_vec(),
+            schedule: WeeklySchedule { day: Weekday::Mon, hour: 8 },
+            recipients: Vec::new(),
+            created_at: Utc.with_ymd_and_hms(2025, 6, 1, 0, 0, 0).unwrap(),
+            last_scheduled_for: None,
+        }
+    }
+
+    #[test]
+    fn test_weekly_report_matches_golden_files() {
+        let period = ReportPeriod::week_ending(Utc.with_ymd_and_hms(2025, 7, 7, 8, 0, 0).unwrap());
+        let mut data = fixture_data(&period);
+        data.usage.insert(crate::metering::UsageEvent::ResourceCreated, 12);
+
+        assert_eq!(render_html(&report(), &period, &data), include_str!("fixtures/weekly.html"));
+        let csv = render_csv(ReportSection::TopResources, &data).unwrap();
+        assert_eq!(csv.name, "top-resources.csv");
+        assert_eq!(String::from_utf8(csv.content).unwrap(), include_str!("fixtures/top-resources.csv"));
+
+        data.slos.clear();
+        assert!(render_csv(ReportSection::SloSummary, &data).is_none());
+        assert!(render_html(&report(), &period, &data).contains("<p>No SLOs are tracked for this scope.</p>"));
+    }
+}
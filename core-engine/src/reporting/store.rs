@@ -0,0 +1,349 @@
+use std::collections::HashMap;
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::types::Json;
+use sqlx::PgPool; // CockroachDB uses PostgreSQL protocol
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use super::{
+    ArtifactInfo, Delivery, Recipient, ReportArtifact, ReportDefinition, ReportPeriod, ReportRun, ReportRunStatus,
+    ReportSection, ReportStore, RunTrigger, WeeklySchedule,
+};
+use crate::error::{AppError, AppResult};
+
+#[derive(sqlx::FromRow)]
+struct ReportRow {
+    id: Uuid,
+    tenant_id: Uuid,
+    project_id: Option<Uuid>,
+    name: String,
+    sections: Json<Vec<ReportSection>>,
+    schedule: Json<WeeklySchedule>,
+    recipients: Json<Vec<Recipient>>,
+    created_at: DateTime<Utc>,
+    last_scheduled_for: Option<DateTime<Utc>>,
+}
+
+impl From<ReportRow> for ReportDefinition {
+    fn from(row: ReportRow) -> Self {
+        Self {
+            id: row.id,
+            tenant_id: row.tenant_id,
+            project_id: row.project_id,
+            name: row.name,
+            sections: row.sections.0,
+            schedule: row.schedule.0,
+            recipients: row.recipients.0,
+            created_at: row.created_at,
+            last_scheduled_for: row.last_scheduled_for,
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct RunRow {
+    id: Uuid,
+    report_id: Uuid,
+    tenant_id: Uuid,
+    trigger: RunTrigger,
+    period_start: DateTime<Utc>,
+    period_end: DateTime<Utc>,
+    status: ReportRunStatus,
+    error: Option<String>,
+    deliveries: Json<Vec<Delivery>>,
+    artifacts: Json<Vec<ArtifactInfo>>,
+    created_at: DateTime<Utc>,
+}
+
+impl From<RunRow> for ReportRun {
+    fn from(row: RunRow) -> Self {
+        Self {
+            id: row.id,
+            report_id: row.report_id,
+            tenant_id: row.tenant_id,
+            trigger: row.trigger,
+            period: ReportPeriod { start: row.period_start, end: row.period_end },
+            status: row.status,
+            error: row.error,
+            deliveries: row.deliveries.0,
+            artifacts: row.artifacts.0,
+            created_at: row.created_at,
+        }
+    }
+}
+
+const REPORT_COLUMNS: &str = "id, tenant_id, project_id, name, sections, schedule, recipients, created_at, last_scheduled_for";
+const RUN_COLUMNS: &str =
+    "id, report_id, tenant_id, trigger, period_start, period_end, status, error, deliveries, artifacts, created_at";
+
+pub struct PgReportStore {
+    pool: PgPool,
+}
+
+impl PgReportStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl ReportStore for PgReportStore {
+    async fn create_report(&self, report: &ReportDefinition) -> AppResult<()> {
+        sqlx::query(
+            r#"INSERT INTO report_definitions (id, tenant_id, project_id, name, sections, schedule, recipients, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#
+        )
+        .bind(report.id)
+        .bind(report.tenant_id)
+        .bind(report.project_id)
+        .bind(&report.name)
+        .bind(Json(&report.sections))
+        .bind(Json(&report.schedule))
+        .bind(Json(&report.recipients))
+        .bind(report.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(())
+    }
+
+    async fn get_report(&self, tenant_id: Uuid, id: Uuid) -> AppResult<Option<ReportDefinition>> {
+        let row = sqlx::query_as::<_, ReportRow>(&format!(
+            "SELECT {} FROM report_definitions WHERE id = $1 AND tenant_id = $2",
+            REPORT_COLUMNS
+        ))
+        .bind(id)
+        .bind(tenant_id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(row.map(Into::into))
+    }
+
+    async fn list_reports(&self, tenant_id: Option<Uuid>) -> AppResult<Vec<ReportDefinition>> {
+        let rows = sqlx::query_as::<_, ReportRow>(&format!(
+            "SELECT {} FROM report_definitions WHERE ($1::UUID IS NULL OR tenant_id = $1) ORDER BY created_at, id",
+            REPORT_COLUMNS
+        ))
+        .bind(tenant_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+
+    async fn delete_report(&self, tenant_id: Uuid, id: Uuid) -> AppResult<bool> {
+        let result = sqlx::query("DELETE FROM report_definitions WHERE id = $1 AND tenant_id = $2")
+            .bind(id)
+            .bind(tenant_id)
+            .execute(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn claim_slot(&self, id: Uuid, previous: Option<DateTime<Utc>>, slot: DateTime<Utc>) -> AppResult<bool> {
+        let result = sqlx::query(
+            r#"UPDATE report_definitions SET last_scheduled_for = $3
+            WHERE id = $1 AND last_scheduled_for IS NOT DISTINCT FROM $2"#
+        )
+        .bind(id)
+        .bind(previous)
+        .bind(slot)
+        .execute(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn create_run(&self, run: &ReportRun, artifacts: &[ReportArtifact]) -> AppResult<()> {
+        let mut tx = self.pool.begin().await.map_err(AppError::Database)?;
+        sqlx::query(
+            r#"INSERT INTO report_runs
+            (id, report_id, tenant_id, trigger, period_start, period_end, status, error, deliveries, artifacts, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)"#
+        )
+        .bind(run.id)
+        .bind(run.report_id)
+        .bind(run.tenant_id)
+        .bind(run.trigger)
+        .bind(run.period.start)
+        .bind(run.period.end)
+        .bind(run.status)
+        .bind(&run.error)
+        .bind(Json(&run.deliveries))
+        .bind(Json(&run.artifacts))
+        .bind(run.created_at)
+        .execute(&mut *tx)
+        .await
+        .map_err(AppError::Database)?;
+
+        for artifact in artifacts {
+            sqlx::query(
+                r#"INSERT INTO report_run_artifacts (run_id, name, content_type, content)
+                VALUES ($1, $2, $3, $4)"#
+            )
+            .bind(run.id)
+            .bind(&artifact.name)
+            .bind(&artifact.content_type)
+            .bind(&artifact.content)
+            .execute(&mut *tx)
+            .await
+            .map_err(AppError::Database)?;
+        }
+        tx.commit().await.map_err(AppError::Database)?;
+
+        Ok(())
+    }
+
+    async fn list_runs(&self, tenant_id: Uuid, report_id: Uuid, limit: i64) -> AppResult<Vec<ReportRun>> {
+        let rows = sqlx::query_as::<_, RunRow>(&format!(
+            "SELECT {} FROM report_runs WHERE tenant_id = $1 AND report_id = $2 ORDER BY created_at DESC, id LIMIT $3",
+            RUN_COLUMNS
+        ))
+        .bind(tenant_id)
+        .bind(report_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+
+    async fn get_run(&self, tenant_id: Uuid, run_id: Uuid) -> AppResult<Option<ReportRun>> {
+        let row = sqlx::query_as::<_, RunRow>(&format!(
+            "SELECT {} FROM report_runs WHERE id = $1 AND tenant_id = $2",
+            RUN_COLUMNS
+        ))
+        .bind(run_id)
+        .bind(tenant_id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(row.map(Into::into))
+    }
+
+    async fn get_artifact(&self, tenant_id: Uuid, run_id: Uuid, name: &str) -> AppResult<Option<ReportArtifact>> {
+        let row: Option<(String, String, Vec<u8>)> = sqlx::query_as(
+            r#"SELECT a.name, a.content_type, a.content FROM report_run_artifacts a
+            JOIN report_runs r ON r.id = a.run_id
+            WHERE a.run_id = $1 AND a.name = $2 AND r.tenant_id = $3"#
+        )
+        .bind(run_id)
+        .bind(name)
+        .bind(tenant_id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(row.map(|(name, content_type, content)| ReportArtifact { name, content_type, content }))
+    }
+}
+
+#[derive(Default)]
+struct InMemoryState {
+    reports: HashMap<Uuid, ReportDefinition>,
+    runs: Vec<ReportRun>,
+    artifacts: HashMap<(Uuid, String), ReportArtifact>,
+}
+
+#[derive(Default)]
+pub struct InMemoryReportStore {
+    state: Mutex<InMemoryState>,
+}
+
+impl InMemoryReportStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl ReportStore for InMemoryReportStore {
+    async fn create_report(&self, report: &ReportDefinition) -> AppResult<()> {
+        self.state.lock().await.reports.insert(report.id, report.clone());
+        Ok(())
+    }
+
+    async fn get_report(&self, tenant_id: Uuid, id: Uuid) -> AppResult<Option<ReportDefinition>> {
+        Ok(self.state.lock().await.reports.get(&id).filter(|r| r.tenant_id == tenant_id).cloned())
+    }
+
+    async fn list_reports(&self, tenant_id: Option<Uuid>) -> AppResult<Vec<ReportDefinition>> {
+        let state = self.state.lock().await;
+        let mut reports: Vec<_> = state
+            .reports
+            .values()
+            .filter(|r| tenant_id.is_none_or(|t| r.tenant_id == t))
+            .cloned()
+            .collect();
+        reports.sort_by_key(|r| (r.created_at, r.id));
+        Ok(reports)
+    }
+
+    async fn delete_report(&self, tenant_id: Uuid, id: Uuid) -> AppResult<bool> {
+        let mut state = self.state.lock().await;
+        if state.reports.get(&id).is_none_or(|r| r.tenant_id != tenant_id) {
+            return Ok(false);
+        }
+        state.reports.remove(&id);
+        let runs: Vec<Uuid> = state.runs.iter().filter(|r| r.report_id == id).map(|r| r.id).collect();
+        state.runs.retain(|r| r.report_id != id);
+        state.artifacts.retain(|(run_id, _), _| !runs.contains(run_id));
+        Ok(true)
+    }
+
+    async fn claim_slot(&self, id: Uuid, previous: Option<DateTime<Utc>>, slot: DateTime<Utc>) -> AppResult<bool> {
+        let mut state = self.state.lock().await;
+        match state.reports.get_mut(&id) {
+            Some(report) if report.last_scheduled_for == previous => {
+                report.last_scheduled_for = Some(slot);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn create_run(&self, run: &ReportRun, artifacts: &[ReportArtifact]) -> AppResult<()> {
+        let mut state = self.state.lock().await;
+        state.runs.push(run.clone());
+        for artifact in artifacts {
+            state.artifacts.insert((run.id, artifact.name.clone()), artifact.clone());
+        }
+        Ok(())
+    }
+
+    async fn list_runs(&self, tenant_id: Uuid, report_id: Uuid, limit: i64) -> AppResult<Vec<ReportRun>> {
+        let state = self.state.lock().await;
+        let mut runs: Vec<_> = state
+            .runs
+            .iter()
+            .filter(|r| r.tenant_id == tenant_id && r.report_id == report_id)
+            .cloned()
+            .collect();
+        runs.sort_by(|a, b| b.created_at.cmp(&a.created_at).then_with(|| a.id.cmp(&b.id)));
+        runs.truncate(limit.max(0) as usize);
+        Ok(runs)
+    }
+
+    async fn get_run(&self, tenant_id: Uuid, run_id: Uuid) -> AppResult<Option<ReportRun>> {
+        Ok(self.state.lock().await.runs.iter().find(|r| r.id == run_id && r.tenant_id == tenant_id).cloned())
+    }
+
+    async fn get_artifact(&self, tenant_id: Uuid, run_id: Uuid, name: &str) -> AppResult<Option<ReportArtifact>> {
+        let state = self.state.lock().await;
+        if !state.runs.iter().any(|r| r.id == run_id && r.tenant_id == tenant_id) {
+            return Ok(None);
+        }
+        Ok(state.artifacts.get(&(run_id, name.to_string())).cloned())
+    }
+}
@@ -0,0 +1,600 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration as StdDuration;
+
+use axum::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::api::export::ExportStorage;
+use crate::error::{AppError, AppResult};
+
+pub mod store;
+
+pub use store::{InMemoryRetentionStore, InMemoryRetentionTarget, PgRetentionStore};
+
+const DEFAULT_BATCH_SIZE: usize = 500;
+const DEFAULT_MAX_BATCHES_PER_RUN: usize = 200;
+const DEFAULT_MAX_ROWS_PER_SECOND: u32 = 1000;
+const MAX_RETAIN_DAYS: i64 = 3650;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "VARCHAR", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum DataCategory {
+    AuditEvents,
+    Metrics,
+    Traces,
+    WorkflowRuns,
+    FlowLogs,
+}
+
+impl DataCategory {
+    pub const ALL: &'static [DataCategory] = &[
+        DataCategory::AuditEvents,
+        DataCategory::Metrics,
+        DataCategory::Traces,
+        DataCategory::WorkflowRuns,
+        DataCategory::FlowLogs,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DataCategory::AuditEvents => "audit_events",
+            DataCategory::Metrics => "metrics",
+            DataCategory::Traces => "traces",
+            DataCategory::WorkflowRuns => "workflow_runs",
+            DataCategory::FlowLogs => "flow_logs",
+        }
+    }
+
+    // Audit logs are kept two years and metrics thirteen months, as legal requires
+    pub fn default_policy(&self) -> RetentionPolicy {
+        let retain_days = match self {
+            DataCategory::AuditEvents => 730,
+            DataCategory::Metrics => 396,
+            DataCategory::Traces => 30,
+            DataCategory::WorkflowRuns => 180,
+            DataCategory::FlowLogs => 90,
+        };
+        RetentionPolicy { retain_days, action: RetentionAction::Delete }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "VARCHAR", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum RetentionAction {
+    Delete,
+    // Written to the archive sink before it is deleted
+    Archive,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    pub retain_days: i64,
+    pub action: RetentionAction,
+}
+
+impl RetentionPolicy {
+    // Anything recorded strictly before this is expired
+    pub fn cutoff(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        now - Duration::days(self.retain_days)
+    }
+}
+
+// A tenant's override of a category default
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct TenantPolicy {
+    pub tenant_id: Uuid,
+    pub category: DataCategory,
+    pub retain_days: i64,
+    pub action: RetentionAction,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EffectivePolicy {
+    pub category: DataCategory,
+    #[serde(flatten)]
+    pub policy: RetentionPolicy,
+    pub overridden: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PurgePreview {
+    pub category: DataCategory,
+    #[serde(flatten)]
+    pub policy: RetentionPolicy,
+    pub cutoff: DateTime<Utc>,
+    pub would_remove: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExpiredRecord {
+    // Whatever the target needs to find the record again when removing it
+    pub key: String,
+    pub recorded_at: DateTime<Utc>,
+    pub data: Value,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "VARCHAR", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum PurgeStatus {
+    Running,
+    Completed,
+    // Stopped at the per-run batch budget; the next run carries on
+    Incomplete,
+    Failed,
+}
+
+// Where a purge has got to, updated after every batch
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct PurgeProgress {
+    pub tenant_id: Uuid,
+    pub category: DataCategory,
+    pub execution_id: Uuid,
+    pub status: PurgeStatus,
+    pub cutoff: DateTime<Utc>,
+    pub batches: i64,
+    pub removed: i64,
+    pub updated_at: DateTime<Utc>,
+}
+
+// One entry in the append-only compliance log
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct PurgeExecution {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub category: DataCategory,
+    pub action: RetentionAction,
+    pub retain_days: i64,
+    pub cutoff: DateTime<Utc>,
+    pub status: PurgeStatus,
+    pub batches: i64,
+    pub removed: i64,
+    // Oldest and newest removed records
+    pub oldest_removed_at: Option<DateTime<Utc>>,
+    pub newest_removed_at: Option<DateTime<Utc>>,
+    pub error: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+}
+
+#[async_trait]
+pub trait RetentionStore: Send + Sync {
+    async fn policies(&self, tenant_id: Uuid) -> AppResult<Vec<TenantPolicy>>;
+    async fn set_policy(&self, policy: &TenantPolicy) -> AppResult<()>;
+    async fn delete_policy(&self, tenant_id: Uuid, category: DataCategory) -> AppResult<bool>;
+    async fn save_progress(&self, progress: &PurgeProgress) -> AppResult<()>;
+    async fn progress(&self, tenant_id: Uuid) -> AppResult<Vec<PurgeProgress>>;
+    // There is deliberately no way to change or remove an execution once appended
+    async fn append_execution(&self, execution: &PurgeExecution) -> AppResult<()>;
+    async fn executions(&self, tenant_id: Uuid, limit: i64) -> AppResult<Vec<PurgeExecution>>;
+}
+
+// Implemented by each module that owns data subject to retention
+#[async_trait]
+pub trait RetentionTarget: Send + Sync {
+    fn category(&self) -> DataCategory;
+    // Tenants that have any data in this category
+    async fn tenants(&self) -> AppResult<Vec<Uuid>>;
+    async fn count_expired(&self, tenant_id: Uuid, cutoff: DateTime<Utc>) -> AppResult<u64>;
+    // Up to `limit` records recorded before `cutoff`, oldest first
+    async fn expired(&self, tenant_id: Uuid, cutoff: DateTime<Utc>, limit: usize) -> AppResult<Vec<ExpiredRecord>>;
+    async fn remove(&self, tenant_id: Uuid, records: &[ExpiredRecord]) -> AppResult<u64>;
+}
+
+#[async_trait]
+pub trait ArchiveSink: Send + Sync {
+    async fn archive(&self, tenant_id: Uuid, category: DataCategory, records: &[ExpiredRecord]) -> AppResult<()>;
+}
+
+// Archives each batch as a JSON-lines object in export storage
+pub struct StorageArchive {
+    storage: Arc<dyn ExportStorage>,
+    prefix: String,
+}
+
+impl StorageArchive {
+    pub fn new(storage: Arc<dyn ExportStorage>) -> Self {
+        Self { storage, prefix: "retention".to_string() }
+    }
+
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+}
+
+#[async_trait]
+impl ArchiveSink for StorageArchive {
+    async fn archive(&self, tenant_id: Uuid, category: DataCategory, records: &[ExpiredRecord]) -> AppResult<()> {
+        let mut body = String::new();
+        for record in records {
+            let line = serde_json::to_string(record).map_err(|e| AppError::Serialization(e.to_string()))?;
+            body.push_str(&line);
+            body.push('\n');
+        }
+        let batch_id = Uuid::new_v4();
+        let path = std::env::temp_dir().join(format!("sirsi-retention-{}.jsonl", batch_id));
+        tokio::fs::write(&path, body)
+            .await
+            .map_err(|e| AppError::Internal(format!("Failed to spool archive batch: {}", e)))?;
+        let key = format!("{}/{}/{}/{}.jsonl", self.prefix, tenant_id, category.as_str(), batch_id);
+        let result = self.storage.upload(&key, &path, "application/x-ndjson").await;
+        let _ = tokio::fs::remove_file(&path).await;
+        result
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PolicyRequest {
+    pub retain_days: i64,
+    #[serde(default = "default_action")]
+    pub action: RetentionAction,
+}
+
+fn default_action() -> RetentionAction {
+    RetentionAction::Delete
+}
+
+pub struct RetentionService {
+    store: Arc<dyn RetentionStore>,
+    targets: Vec<Arc<dyn RetentionTarget>>,
+    archive: Option<Arc<dyn ArchiveSink>>,
+    batch_size: usize,
+    max_batches_per_run: usize,
+    max_rows_per_second: Option<u32>,
+}
+
+impl RetentionService {
+    pub fn new(store: Arc<dyn RetentionStore>) -> Self {
+        Self {
+            store,
+            targets: Vec::new(),
+            archive: None,
+            batch_size: DEFAULT_BATCH_SIZE,
+            max_batches_per_run: DEFAULT_MAX_BATCHES_PER_RUN,
+            max_rows_per_second: Some(DEFAULT_MAX_ROWS_PER_SECOND),
+        }
+    }
+
+    pub fn with_target(mut self, target: Arc<dyn RetentionTarget>) -> Self {
+        self.targets.retain(|t| t.category() != target.category());
+        self.targets.push(target);
+        self
+    }
+
+    pub fn with_archive(mut self, archive: Arc<dyn ArchiveSink>) -> Self {
+        self.archive = Some(archive);
+        self
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    pub fn with_max_batches_per_run(mut self, max_batches: usize) -> Self {
+        self.max_batches_per_run = max_batches.max(1);
+        self
+    }
+
+    // None removes the throttle entirely
+    pub fn with_max_rows_per_second(mut self, rows: Option<u32>) -> Self {
+        self.max_rows_per_second = rows.filter(|r| *r > 0);
+        self
+    }
+
+    pub async fn policies(&self, tenant_id: Uuid) -> AppResult<Vec<EffectivePolicy>> {
+        let overrides: HashMap<DataCategory, TenantPolicy> =
+            self.store.policies(tenant_id).await?.into_iter().map(|p| (p.category, p)).collect();
+        Ok(DataCategory::ALL
+            .iter()
+            .map(|category| match overrides.get(category) {
+                Some(p) => EffectivePolicy {
+                    category: *category,
+                    policy: RetentionPolicy { retain_days: p.retain_days, action: p.action },
+                    overridden: true,
+                },
+                None => EffectivePolicy { category: *category, policy: category.default_policy(), overridden: false },
+            })
+            .collect())
+    }
+
+    pub async fn effective_policy(&self, tenant_id: Uuid, category: DataCategory) -> AppResult<RetentionPolicy> {
+        let policies = self.policies(tenant_id).await?;
+        Ok(policies
+            .into_iter()
+            .find(|p| p.category == category)
+            .map(|p| p.policy)
+            .unwrap_or_else(|| category.default_policy()))
+    }
+
+    pub async fn set_policy(
+        &self,
+        tenant_id: Uuid,
+        category: DataCategory,
+        request: PolicyRequest,
+        now: DateTime<Utc>,
+    ) -> AppResult<TenantPolicy> {
+        if !(1..=MAX_RETAIN_DAYS).contains(&request.retain_days) {
+            return Err(AppError::Validation(format!("retain_days must be between 1 and {}", MAX_RETAIN_DAYS)));
+        }
+        if request.action == RetentionAction::Archive && self.archive.is_none() {
+            return Err(AppError::Configuration("No archive storage is configured".into()));
+        }
+        let policy = TenantPolicy { tenant_id, category, retain_days: request.retain_days, action: request.action, updated_at: now };
+        self.store.set_policy(&policy).await?;
+        Ok(policy)
+    }
+
+    pub async fn reset_policy(&self, tenant_id: Uuid, category: DataCategory) -> AppResult<()> {
+        if !self.store.delete_policy(tenant_id, category).await? {
+            return Err(AppError::NotFound(format!("No {} retention override", category.as_str())));
+        }
+        Ok(())
+    }
+
+    pub async fn progress(&self, tenant_id: Uuid) -> AppResult<Vec<PurgeProgress>> {
+        self.store.progress(tenant_id).await
+    }
+
+    pub async fn executions(&self, tenant_id: Uuid, limit: i64) -> AppResult<Vec<PurgeExecution>> {
+        self.store.executions(tenant_id, limit).await
+    }
+
+    // Counts with the same cutoff a purge at `now` would use, without touching anything
+    pub async fn dry_run(&self, tenant_id: Uuid, now: DateTime<Utc>) -> AppResult<Vec<PurgePreview>> {
+        let mut previews = Vec::new();
+        for target in &self.targets {
+            let policy = self.effective_policy(tenant_id, target.category()).await?;
+            let cutoff = policy.cutoff(now);
+            previews.push(PurgePreview {
+                category: target.category(),
+                policy,
+                cutoff,
+                would_remove: target.count_expired(tenant_id, cutoff).await?,
+            });
+        }
+        previews.sort_by_key(|p| p.category);
+        Ok(previews)
+    }
+
+    pub async fn purge_tenant(&self, tenant_id: Uuid, now: DateTime<Utc>) -> AppResult<Vec<PurgeExecution>> {
+        let mut executions = Vec::new();
+        for target in &self.targets {
+            if let Some(execution) = self.purge_target(target.as_ref(), tenant_id, now).await? {
+                executions.push(execution);
+            }
+        }
+        Ok(executions)
+    }
+
+    pub async fn purge_once(&self, now: DateTime<Utc>) -> AppResult<Vec<PurgeExecution>> {
+        let mut executions = Vec::new();
+        for target in &self.targets {
+            for tenant_id in target.tenants().await? {
+                if let Some(execution) = self.purge_target(target.as_ref(), tenant_id, now).await? {
+                    executions.push(execution);
+                }
+            }
+        }
+        Ok(executions)
+    }
+
+    // Only purges that removed something or failed are logged, so idle runs don't flood the log
+    async fn purge_target(
+        &self,
+        target: &dyn RetentionTarget,
+        tenant_id: Uuid,
+        now: DateTime<Utc>,
+    ) -> AppResult<Option<PurgeExecution>> {
+        let category = target.category();
+        let policy = self.effective_policy(tenant_id, category).await?;
+        let started_at = Utc::now();
+        let mut execution = PurgeExecution {
+            id: Uuid::new_v4(),
+            tenant_id,
+            category,
+            action: policy.action,
+            retain_days: policy.retain_days,
+            cutoff: policy.cutoff(now),
+            status: PurgeStatus::Running,
+            batches: 0,
+            removed: 0,
+            oldest_removed_at: None,
+            newest_removed_at: None,
+            error: None,
+            started_at,
+            finished_at: started_at,
+        };
+
+        match self.run_batches(target, &policy, &mut execution).await {
+            Ok(status) => execution.status = status,
+            Err(e) => {
+                warn!("Retention purge of {} for tenant {} failed: {}", category.as_str(), tenant_id, e);
+                execution.status = PurgeStatus::Failed;
+                execution.error = Some(e.to_string());
+            }
+        }
+        execution.finished_at = Utc::now();
+        if execution.batches == 0 && execution.status != PurgeStatus::Failed {
+            return Ok(None);
+        }
+        self.save_progress(&execution).await?;
+        self.store.append_execution(&execution).await?;
+        Ok(Some(execution))
+    }
+
+    async fn run_batches(
+        &self,
+        target: &dyn RetentionTarget,
+        policy: &RetentionPolicy,
+        execution: &mut PurgeExecution,
+    ) -> AppResult<PurgeStatus> {
+        let archive = match policy.action {
+            RetentionAction::Archive => Some(
+                self.archive
+                    .as_ref()
+                    .ok_or_else(|| AppError::Configuration("No archive storage is configured".into()))?,
+            ),
+            RetentionAction::Delete => None,
+        };
+
+        while (execution.batches as usize) < self.max_batches_per_run {
+            let records = target.expired(execution.tenant_id, execution.cutoff, self.batch_size).await?;
+            if records.is_empty() {
+                return Ok(PurgeStatus::Completed);
+            }
+            if let Some(archive) = archive {
+                archive.archive(execution.tenant_id, execution.category, &records).await?;
+            }
+            let removed = target.remove(execution.tenant_id, &records).await?;
+            let oldest = records.iter().map(|r| r.recorded_at).min();
+            let newest = records.iter().map(|r| r.recorded_at).max();
+            // Batches come oldest first, so the first one holds the oldest record
+            execution.oldest_removed_at = execution.oldest_removed_at.or(oldest);
+            execution.newest_removed_at = execution.newest_removed_at.max(newest);
+            execution.batches += 1;
+            execution.removed += removed as i64;
+            self.save_progress(execution).await?;
+
+            if records.len() < self.batch_size {
+                return Ok(PurgeStatus::Completed);
+            }
+            if let Some(rate) = self.max_rows_per_second {
+                tokio::time::sleep(StdDuration::from_secs_f64(records.len() as f64 / rate as f64)).await;
+            }
+        }
+        Ok(PurgeStatus::Incomplete)
+    }
+
+    async fn save_progress(&self, execution: &PurgeExecution) -> AppResult<()> {
+        self.store
+            .save_progress(&PurgeProgress {
+                tenant_id: execution.tenant_id,
+                category: execution.category,
+                execution_id: execution.id,
+                status: execution.status,
+                cutoff: execution.cutoff,
+                batches: execution.batches,
+                removed: execution.removed,
+                updated_at: Utc::now(),
+            })
+            .await
+    }
+
+    pub fn spawn_purger(self: Arc<Self>, interval: StdDuration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                match self.purge_once(Utc::now()).await {
+                    Ok(executions) if !executions.is_empty() => {
+                        let removed: i64 = executions.iter().map(|e| e.removed).sum();
+                        info!("Retention purge removed {} records in {} executions", removed, executions.len())
+                    }
+                    Ok(_) => {}
+                    Err(e) => warn!("Retention purge failed: {}", e),
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn seeded(tenant_id: Uuid, category: DataCategory, now: DateTime<Utc>, ages_in_days: &[i64]) -> Arc<InMemoryRetentionTarget> {
+        let target = Arc::new(InMemoryRetentionTarget::new(category));
+        for (i, age) in ages_in_days.iter().enumerate() {
+            target.insert(tenant_id, &i.to_string(), now - Duration::days(*age), json!({ "n": i }));
+        }
+        target
+    }
+
+    #[tokio::test]
+    async fn test_batches_split_on_batch_size_and_stop_at_the_cutoff() {
+        let tenant_id = Uuid::new_v4();
+        let now = Utc::now();
+        // Exactly two full batches are expired; the record at the cutoff itself is kept
+        let mut ages = vec![800; 6];
+        ages.extend([730, 10]);
+        let target = seeded(tenant_id, DataCategory::AuditEvents, now, &ages);
+        let store = Arc::new(InMemoryRetentionStore::new());
+        let service = RetentionService::new(store.clone())
+            .with_target(target.clone())
+            .with_batch_size(3)
+            .with_max_rows_per_second(None);
+
+        let executions = service.purge_once(now).await.unwrap();
+        assert_eq!(executions.len(), 1);
+        assert_eq!(executions[0].status, PurgeStatus::Completed);
+        assert_eq!(executions[0].batches, 2);
+        assert_eq!(executions[0].removed, 6);
+        assert_eq!(target.len(tenant_id), 2);
+        assert_eq!(store.executions(tenant_id, 10).await.unwrap().len(), 1);
+
+        // Nothing left to remove, so nothing new is logged
+        assert!(service.purge_once(now).await.unwrap().is_empty());
+
+        let target = seeded(tenant_id, DataCategory::AuditEvents, now, &[800; 7]);
+        let service = RetentionService::new(store.clone())
+            .with_target(target.clone())
+            .with_batch_size(3)
+            .with_max_batches_per_run(2)
+            .with_max_rows_per_second(None);
+        let first = service.purge_once(now).await.unwrap();
+        assert_eq!((first[0].status, first[0].removed), (PurgeStatus::Incomplete, 6));
+        let second = service.purge_once(now).await.unwrap();
+        assert_eq!((second[0].status, second[0].batches, second[0].removed), (PurgeStatus::Completed, 1, 1));
+        assert_eq!(target.len(tenant_id), 0);
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_matches_purge_and_tenant_override_beats_default() {
+        let now = Utc::now();
+        let (overridden, defaulted) = (Uuid::new_v4(), Uuid::new_v4());
+        let ages = [10, 40, 100, 200, 400, 500];
+        let target = Arc::new(InMemoryRetentionTarget::new(DataCategory::Metrics));
+        for tenant_id in [overridden, defaulted] {
+            for (i, age) in ages.iter().enumerate() {
+                target.insert(tenant_id, &i.to_string(), now - Duration::days(*age), json!({}));
+            }
+        }
+        let service = RetentionService::new(Arc::new(InMemoryRetentionStore::new()))
+            .with_target(target.clone())
+            .with_batch_size(2)
+            .with_max_rows_per_second(None);
+        service
+            .set_policy(overridden, DataCategory::Metrics, PolicyRequest { retain_days: 30, action: RetentionAction::Delete }, now)
+            .await
+            .unwrap();
+        assert_eq!(service.effective_policy(overridden, DataCategory::Metrics).await.unwrap().retain_days, 30);
+        assert_eq!(service.effective_policy(defaulted, DataCategory::Metrics).await.unwrap().retain_days, 396);
+
+        let previews = service.dry_run(overridden, now).await.unwrap();
+        assert_eq!(previews[0].would_remove, 5);
+        assert_eq!(service.dry_run(defaulted, now).await.unwrap()[0].would_remove, 2);
+        // The dry run changed nothing
+        assert_eq!(target.len(overridden), 6);
+
+        let executions = service.purge_tenant(overridden, now).await.unwrap();
+        assert_eq!(executions[0].removed as u64, previews[0].would_remove);
+        assert_eq!(executions[0].cutoff, previews[0].cutoff);
+        assert_eq!(target.len(overridden), 1);
+        assert_eq!(target.len(defaulted), 6);
+
+        let executions = service.purge_tenant(defaulted, now).await.unwrap();
+        assert_eq!(executions[0].removed, 2);
+    }
+}
@@ -0,0 +1,266 @@
+use std::collections::HashMap;
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use sqlx::PgPool; // CockroachDB uses PostgreSQL protocol
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use super::{DataCategory, ExpiredRecord, PurgeExecution, PurgeProgress, RetentionStore, RetentionTarget, TenantPolicy};
+use crate::error::{AppError, AppResult};
+
+const PROGRESS_COLUMNS: &str = "tenant_id, category, execution_id, status, cutoff, batches, removed, updated_at";
+const EXECUTION_COLUMNS: &str = "id, tenant_id, category, action, retain_days, cutoff, status, batches, removed, \
+    oldest_removed_at, newest_removed_at, error, started_at, finished_at";
+
+pub struct PgRetentionStore {
+    pool: PgPool,
+}
+
+impl PgRetentionStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl RetentionStore for PgRetentionStore {
+    async fn policies(&self, tenant_id: Uuid) -> AppResult<Vec<TenantPolicy>> {
+        let policies = sqlx::query_as::<_, TenantPolicy>(
+            r#"SELECT tenant_id, category, retain_days, action, updated_at
+            FROM retention_policies WHERE tenant_id = $1 ORDER BY category"#
+        )
+        .bind(tenant_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(policies)
+    }
+
+    async fn set_policy(&self, policy: &TenantPolicy) -> AppResult<()> {
+        sqlx::query(
+            r#"UPSERT INTO retention_policies (tenant_id, category, retain_days, action, updated_at)
+            VALUES ($1, $2, $3, $4, $5)"#
+        )
+        .bind(policy.tenant_id)
+        .bind(policy.category)
+        .bind(policy.retain_days)
+        .bind(policy.action)
+        .bind(policy.updated_at)
+        .execute(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(())
+    }
+
+    async fn delete_policy(&self, tenant_id: Uuid, category: DataCategory) -> AppResult<bool> {
+        let result = sqlx::query("DELETE FROM retention_policies WHERE tenant_id = $1 AND category = $2")
+            .bind(tenant_id)
+            .bind(category)
+            .execute(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn save_progress(&self, progress: &PurgeProgress) -> AppResult<()> {
+        let sql = format!("UPSERT INTO retention_purge_progress ({}) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)", PROGRESS_COLUMNS);
+        sqlx::query(&sql)
+            .bind(progress.tenant_id)
+            .bind(progress.category)
+            .bind(progress.execution_id)
+            .bind(progress.status)
+            .bind(progress.cutoff)
+            .bind(progress.batches)
+            .bind(progress.removed)
+            .bind(progress.updated_at)
+            .execute(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(())
+    }
+
+    async fn progress(&self, tenant_id: Uuid) -> AppResult<Vec<PurgeProgress>> {
+        let sql = format!("SELECT {} FROM retention_purge_progress WHERE tenant_id = $1 ORDER BY category", PROGRESS_COLUMNS);
+        let progress = sqlx::query_as::<_, PurgeProgress>(&sql)
+            .bind(tenant_id)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(progress)
+    }
+
+    async fn append_execution(&self, execution: &PurgeExecution) -> AppResult<()> {
+        let sql = format!(
+            "INSERT INTO retention_purge_executions ({}) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)",
+            EXECUTION_COLUMNS
+        );
+        sqlx::query(&sql)
+            .bind(execution.id)
+            .bind(execution.tenant_id)
+            .bind(execution.category)
+            .bind(execution.action)
+            .bind(execution.retain_days)
+            .bind(execution.cutoff)
+            .bind(execution.status)
+            .bind(execution.batches)
+            .bind(execution.removed)
+            .bind(execution.oldest_removed_at)
+            .bind(execution.newest_removed_at)
+            .bind(&execution.error)
+            .bind(execution.started_at)
+            .bind(execution.finished_at)
+            .execute(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(())
+    }
+
+    async fn executions(&self, tenant_id: Uuid, limit: i64) -> AppResult<Vec<PurgeExecution>> {
+        let sql = format!(
+            "SELECT {} FROM retention_purge_executions WHERE tenant_id = $1 ORDER BY started_at DESC LIMIT $2",
+            EXECUTION_COLUMNS
+        );
+        let executions = sqlx::query_as::<_, PurgeExecution>(&sql)
+            .bind(tenant_id)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(executions)
+    }
+}
+
+#[derive(Default)]
+struct InMemoryState {
+    policies: HashMap<(Uuid, DataCategory), TenantPolicy>,
+    progress: HashMap<(Uuid, DataCategory), PurgeProgress>,
+    executions: Vec<PurgeExecution>,
+}
+
+#[derive(Default)]
+pub struct InMemoryRetentionStore {
+    state: Mutex<InMemoryState>,
+}
+
+impl InMemoryRetentionStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl RetentionStore for InMemoryRetentionStore {
+    async fn policies(&self, tenant_id: Uuid) -> AppResult<Vec<TenantPolicy>> {
+        let state = self.state.lock().await;
+        let mut policies: Vec<TenantPolicy> = state.policies.values().filter(|p| p.tenant_id == tenant_id).cloned().collect();
+        policies.sort_by_key(|p| p.category);
+        Ok(policies)
+    }
+
+    async fn set_policy(&self, policy: &TenantPolicy) -> AppResult<()> {
+        self.state.lock().await.policies.insert((policy.tenant_id, policy.category), policy.clone());
+        Ok(())
+    }
+
+    async fn delete_policy(&self, tenant_id: Uuid, category: DataCategory) -> AppResult<bool> {
+        Ok(self.state.lock().await.policies.remove(&(tenant_id, category)).is_some())
+    }
+
+    async fn save_progress(&self, progress: &PurgeProgress) -> AppResult<()> {
+        self.state.lock().await.progress.insert((progress.tenant_id, progress.category), progress.clone());
+        Ok(())
+    }
+
+    async fn progress(&self, tenant_id: Uuid) -> AppResult<Vec<PurgeProgress>> {
+        let state = self.state.lock().await;
+        let mut progress: Vec<PurgeProgress> = state.progress.values().filter(|p| p.tenant_id == tenant_id).cloned().collect();
+        progress.sort_by_key(|p| p.category);
+        Ok(progress)
+    }
+
+    async fn append_execution(&self, execution: &PurgeExecution) -> AppResult<()> {
+        self.state.lock().await.executions.push(execution.clone());
+        Ok(())
+    }
+
+    async fn executions(&self, tenant_id: Uuid, limit: i64) -> AppResult<Vec<PurgeExecution>> {
+        let state = self.state.lock().await;
+        Ok(state
+            .executions
+            .iter()
+            .rev()
+            .filter(|e| e.tenant_id == tenant_id)
+            .take(limit.max(0) as usize)
+            .cloned()
+            .collect())
+    }
+}
+
+// For categories whose module keeps its data in process
+pub struct InMemoryRetentionTarget {
+    category: DataCategory,
+    records: std::sync::Mutex<HashMap<Uuid, Vec<ExpiredRecord>>>,
+}
+
+impl InMemoryRetentionTarget {
+    pub fn new(category: DataCategory) -> Self {
+        Self { category, records: std::sync::Mutex::new(HashMap::new()) }
+    }
+
+    pub fn insert(&self, tenant_id: Uuid, key: &str, recorded_at: DateTime<Utc>, data: Value) {
+        let mut records = self.records.lock().unwrap();
+        records.entry(tenant_id).or_default().push(ExpiredRecord { key: key.to_string(), recorded_at, data });
+    }
+
+    pub fn len(&self, tenant_id: Uuid) -> usize {
+        self.records.lock().unwrap().get(&tenant_id).map_or(0, Vec::len)
+    }
+}
+
+#[async_trait]
+impl RetentionTarget for InMemoryRetentionTarget {
+    fn category(&self) -> DataCategory {
+        self.category
+    }
+
+    async fn tenants(&self) -> AppResult<Vec<Uuid>> {
+        let mut tenants: Vec<Uuid> = self.records.lock().unwrap().keys().copied().collect();
+        tenants.sort();
+        Ok(tenants)
+    }
+
+    async fn count_expired(&self, tenant_id: Uuid, cutoff: DateTime<Utc>) -> AppResult<u64> {
+        let records = self.records.lock().unwrap();
+        Ok(records.get(&tenant_id).map_or(0, |r| r.iter().filter(|r| r.recorded_at < cutoff).count() as u64))
+    }
+
+    async fn expired(&self, tenant_id: Uuid, cutoff: DateTime<Utc>, limit: usize) -> AppResult<Vec<ExpiredRecord>> {
+        let records = self.records.lock().unwrap();
+        let mut expired: Vec<ExpiredRecord> = records
+            .get(&tenant_id)
+            .map(|r| r.iter().filter(|r| r.recorded_at < cutoff).cloned().collect())
+            .unwrap_or_default();
+        expired.sort_by(|a, b| a.recorded_at.cmp(&b.recorded_at).then_with(|| a.key.cmp(&b.key)));
+        expired.truncate(limit);
+        Ok(expired)
+    }
+
+    async fn remove(&self, tenant_id: Uuid, records: &[ExpiredRecord]) -> AppResult<u64> {
+        let mut stored = self.records.lock().unwrap();
+        let Some(tenant_records) = stored.get_mut(&tenant_id) else {
+            return Ok(0);
+        };
+        let before = tenant_records.len();
+        tenant_records.retain(|r| !records.iter().any(|removed| removed.key == r.key));
+        Ok((before - tenant_records.len()) as u64)
+    }
+}
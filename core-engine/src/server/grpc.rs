@@ -1,4 +1,6 @@
+use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
 use tonic::transport::Server;
 use tonic_reflection::server::Builder as ReflectionServerBuilder;
@@ -7,14 +9,19 @@ use tracing::{info, error};
 use crate::agent::{AgentManager, AgentService};
 use crate::agent::context::ContextStore;
 use crate::error::{AppError, AppResult};
+use crate::health::{Heartbeat, HeartbeatCheck, HealthRegistry, RedisCheck, TcpCheck};
 use crate::proto::sirsi::agent::v1::agent_service_server::AgentServiceServer;
 
 pub struct GrpcServer {
     port: u16,
     agent_manager: Arc<RwLock<AgentManager>>,
     context_store: Arc<ContextStore>,
+    heartbeat: Heartbeat,
 }
 
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+const MAX_HEARTBEAT_LAG: Duration = Duration::from_secs(30);
+
 impl GrpcServer {
     pub fn new(port: u16, redis_url: &str) -> AppResult<Self> {
         let context_store = Arc::new(ContextStore::new(redis_url)?);
@@ -25,6 +32,33 @@ impl GrpcServer {
             port,
             agent_manager,
             context_store,
+            heartbeat: Heartbeat::new(),
+        })
+    }
+
+    // Redis and the gRPC listener gate readiness; a slow agent manager only degrades it
+    pub fn health_registry(&self) -> HealthRegistry {
+        let grpc_addr = SocketAddr::from(([127, 0, 0, 1], self.port));
+        HealthRegistry::new()
+            .with_critical("redis", Arc::new(RedisCheck::new(self.context_store.clone())))
+            .with_critical("grpc", Arc::new(TcpCheck::new(grpc_addr)))
+            .with_non_critical(
+                "agent_manager",
+                Arc::new(HeartbeatCheck::new(self.heartbeat.clone(), MAX_HEARTBEAT_LAG)),
+            )
+    }
+
+    // Beats only after getting the manager lock, so a wedged manager shows up as lag
+    fn spawn_heartbeat(&self) -> tokio::task::JoinHandle<()> {
+        let agent_manager = self.agent_manager.clone();
+        let heartbeat = self.heartbeat.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(HEARTBEAT_INTERVAL);
+            loop {
+                ticker.tick().await;
+                let _manager = agent_manager.read().await;
+                heartbeat.beat();
+            }
         })
     }
 
@@ -54,6 +88,7 @@ impl GrpcServer {
             .map_err(|e| AppError::Configuration(format!("Failed to build reflection service: {}", e)))?;
 
         info!("Agent service initialized, starting server...");
+        let heartbeat = self.spawn_heartbeat();
 
         // Start the server
         let result = Server::builder()
@@ -61,6 +96,7 @@ impl GrpcServer {
             .add_service(reflection_service)
             .serve(addr)
             .await;
+        heartbeat.abort();
 
         match result {
             Ok(_) => {
@@ -0,0 +1,355 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use axum::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
+use sqlx::PgPool; // CockroachDB uses PostgreSQL protocol
+use uuid::Uuid;
+
+use super::schema::{Column, ColumnType, Row, TableSchema, Value};
+use crate::error::{AppError, AppResult};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "VARCHAR", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum Dataset {
+    // Current inventory rows; a resource ships again whenever a discovery run updates it
+    InventorySnapshots,
+    // Per-tenant daily usage counters, which keep changing until their day is over
+    DailyMetrics,
+    // Workflow runs across the tenant's projects
+    WorkflowRuns,
+}
+
+impl Dataset {
+    pub const ALL: &'static [Dataset] = &[Dataset::InventorySnapshots, Dataset::DailyMetrics, Dataset::WorkflowRuns];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Dataset::InventorySnapshots => "inventory_snapshots",
+            Dataset::DailyMetrics => "daily_metrics",
+            Dataset::WorkflowRuns => "workflow_runs",
+        }
+    }
+
+    // The schema shipped today. Columns may be added here freely as long as they are nullable;
+    // anything else stops existing exports until a tenant accepts the change.
+    pub fn schema(&self) -> TableSchema {
+        let columns = match self {
+            Dataset::InventorySnapshots => vec![
+                Column::required("resource_id", ColumnType::String),
+                Column::required("provider", ColumnType::String),
+                Column::required("account_id", ColumnType::String),
+                Column::required("region", ColumnType::String),
+                Column::required("resource_type", ColumnType::String),
+                Column::required("resource_key", ColumnType::String),
+                Column::nullable("name", ColumnType::String),
+                Column::nullable("state", ColumnType::String),
+                Column::required("tags", ColumnType::Json),
+                Column::required("attributes", ColumnType::Json),
+                Column::required("first_seen_at", ColumnType::Timestamp),
+                Column::required("last_seen_at", ColumnType::Timestamp),
+                Column::required("updated_at", ColumnType::Timestamp),
+            ],
+            Dataset::DailyMetrics => vec![
+                Column::required("day", ColumnType::Date),
+                Column::required("metric", ColumnType::String),
+                Column::required("count", ColumnType::Int64),
+                Column::required("updated_at", ColumnType::Timestamp),
+            ],
+            Dataset::WorkflowRuns => vec![
+                Column::required("id", ColumnType::String),
+                Column::required("project_id", ColumnType::String),
+                Column::required("run_id", ColumnType::String),
+                Column::required("workflow_id", ColumnType::String),
+                Column::required("status", ColumnType::String),
+                Column::required("trigger_type", ColumnType::String),
+                Column::required("started_at", ColumnType::Timestamp),
+                Column::nullable("finished_at", ColumnType::Timestamp),
+                Column::nullable("duration_ms", ColumnType::Int64),
+                Column::required("failed_tasks", ColumnType::Json),
+                Column::required("updated_at", ColumnType::Timestamp),
+            ],
+        };
+        TableSchema::new(columns)
+    }
+}
+
+// How far an export has got: rows are read in `(updated_at, key)` order, and the next run
+// starts after the last row shipped
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Watermark {
+    pub updated_at: DateTime<Utc>,
+    pub key: String,
+}
+
+impl Default for Watermark {
+    fn default() -> Self {
+        Self { updated_at: DateTime::UNIX_EPOCH, key: String::new() }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceRow {
+    pub watermark: Watermark,
+    pub values: Row,
+}
+
+#[async_trait]
+pub trait DatasetSource: Send + Sync {
+    fn schema(&self, dataset: Dataset) -> TableSchema;
+    // Rows changed after `after` and before `until`, in watermark order
+    async fn read(
+        &self,
+        tenant_id: Uuid,
+        dataset: Dataset,
+        after: &Watermark,
+        until: DateTime<Utc>,
+        limit: i64,
+    ) -> AppResult<Vec<SourceRow>>;
+}
+
+fn json_text(value: &serde_json::Value) -> Value {
+    Value::String(value.to_string())
+}
+
+#[derive(sqlx::FromRow)]
+struct InventoryRow {
+    key: String,
+    provider: String,
+    account_id: String,
+    region: String,
+    resource_type: String,
+    resource_key: String,
+    name: Option<String>,
+    state: Option<String>,
+    tags: Json<serde_json::Value>,
+    attributes: Json<serde_json::Value>,
+    first_seen_at: DateTime<Utc>,
+    last_seen_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl From<InventoryRow> for SourceRow {
+    fn from(row: InventoryRow) -> Self {
+        Self {
+            values: vec![
+                row.key.clone().into(),
+                row.provider.into(),
+                row.account_id.into(),
+                row.region.into(),
+                row.resource_type.into(),
+                row.resource_key.into(),
+                row.name.into(),
+                row.state.into(),
+                json_text(&row.tags),
+                json_text(&row.attributes),
+                row.first_seen_at.into(),
+                row.last_seen_at.into(),
+                row.updated_at.into(),
+            ],
+            watermark: Watermark { updated_at: row.updated_at, key: row.key },
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct MetricRow {
+    key: String,
+    day: NaiveDate,
+    event: String,
+    count: i64,
+    updated_at: DateTime<Utc>,
+}
+
+impl From<MetricRow> for SourceRow {
+    fn from(row: MetricRow) -> Self {
+        Self {
+            values: vec![row.day.into(), row.event.into(), row.count.into(), row.updated_at.into()],
+            watermark: Watermark { updated_at: row.updated_at, key: row.key },
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct RunRow {
+    key: String,
+    project_id: Uuid,
+    run_id: String,
+    workflow_id: String,
+    status: String,
+    trigger_type: String,
+    started_at: DateTime<Utc>,
+    finished_at: Option<DateTime<Utc>>,
+    duration_ms: Option<i64>,
+    failed_tasks: Vec<String>,
+    updated_at: DateTime<Utc>,
+}
+
+impl From<RunRow> for SourceRow {
+    fn from(row: RunRow) -> Self {
+        Self {
+            values: vec![
+                row.key.clone().into(),
+                row.project_id.to_string().into(),
+                row.run_id.into(),
+                row.workflow_id.into(),
+                row.status.into(),
+                row.trigger_type.into(),
+                row.started_at.into(),
+                row.finished_at.into(),
+                row.duration_ms.into(),
+                json_text(&serde_json::json!(row.failed_tasks)),
+                row.updated_at.into(),
+            ],
+            watermark: Watermark { updated_at: row.updated_at, key: row.key },
+        }
+    }
+}
+
+// Reads the datasets from their own tables through the `updated_at` columns CockroachDB keeps
+// current with `ON UPDATE now()`
+pub struct PgDatasetSource {
+    pool: PgPool,
+}
+
+impl PgDatasetSource {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl DatasetSource for PgDatasetSource {
+    fn schema(&self, dataset: Dataset) -> TableSchema {
+        dataset.schema()
+    }
+
+    async fn read(
+        &self,
+        tenant_id: Uuid,
+        dataset: Dataset,
+        after: &Watermark,
+        until: DateTime<Utc>,
+        limit: i64,
+    ) -> AppResult<Vec<SourceRow>> {
+        let rows = match dataset {
+            Dataset::InventorySnapshots => sqlx::query_as::<_, InventoryRow>(
+                r#"SELECT id::STRING AS key, provider, account_id, region, resource_type, resource_key, name, state,
+                    tags, attributes, first_seen_at, last_seen_at, updated_at
+                FROM inventory_resources
+                WHERE tenant_id = $1 AND updated_at < $2 AND (updated_at, id::STRING) > ($3, $4)
+                ORDER BY updated_at, key LIMIT $5"#
+            )
+            .bind(tenant_id)
+            .bind(until)
+            .bind(after.updated_at)
+            .bind(&after.key)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(AppError::Database)?
+            .into_iter()
+            .map(SourceRow::from)
+            .collect(),
+            Dataset::DailyMetrics => sqlx::query_as::<_, MetricRow>(
+                r#"SELECT day::STRING || '/' || event AS key, day, event, count, updated_at
+                FROM usage_daily
+                WHERE tenant_id = $1 AND updated_at < $2 AND (updated_at, day::STRING || '/' || event) > ($3, $4)
+                ORDER BY updated_at, key LIMIT $5"#
+            )
+            .bind(tenant_id)
+            .bind(until)
+            .bind(after.updated_at)
+            .bind(&after.key)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(AppError::Database)?
+            .into_iter()
+            .map(SourceRow::from)
+            .collect(),
+            Dataset::WorkflowRuns => sqlx::query_as::<_, RunRow>(
+                r#"SELECT r.id::STRING AS key, r.project_id, r.run_id, r.workflow_id, r.status, r.trigger_type,
+                    r.started_at, r.finished_at, r.duration_ms, r.failed_tasks, r.updated_at
+                FROM workflow_runs r JOIN projects p ON p.id = r.project_id
+                WHERE p.owner_id = $1 AND r.updated_at < $2 AND (r.updated_at, r.id::STRING) > ($3, $4)
+                ORDER BY r.updated_at, key LIMIT $5"#
+            )
+            .bind(tenant_id)
+            .bind(until)
+            .bind(after.updated_at)
+            .bind(&after.key)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(AppError::Database)?
+            .into_iter()
+            .map(SourceRow::from)
+            .collect(),
+        };
+        Ok(rows)
+    }
+}
+
+#[derive(Default)]
+struct InMemoryDatasets {
+    rows: HashMap<(Uuid, Dataset), HashMap<String, SourceRow>>,
+    schemas: HashMap<Dataset, TableSchema>,
+}
+
+// Rows are put with the watermark they would have in the database
+#[derive(Default)]
+pub struct InMemoryDatasetSource {
+    state: Mutex<InMemoryDatasets>,
+}
+
+impl InMemoryDatasetSource {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Replaces the row with the same key
+    pub fn put(&self, tenant_id: Uuid, dataset: Dataset, row: SourceRow) {
+        let mut state = self.state.lock().unwrap();
+        state.rows.entry((tenant_id, dataset)).or_default().insert(row.watermark.key.clone(), row);
+    }
+
+    // Stands in for a release that changes what a dataset ships
+    pub fn set_schema(&self, dataset: Dataset, schema: TableSchema) {
+        self.state.lock().unwrap().schemas.insert(dataset, schema);
+    }
+}
+
+#[async_trait]
+impl DatasetSource for InMemoryDatasetSource {
+    fn schema(&self, dataset: Dataset) -> TableSchema {
+        self.state.lock().unwrap().schemas.get(&dataset).cloned().unwrap_or_else(|| dataset.schema())
+    }
+
+    async fn read(
+        &self,
+        tenant_id: Uuid,
+        dataset: Dataset,
+        after: &Watermark,
+        until: DateTime<Utc>,
+        limit: i64,
+    ) -> AppResult<Vec<SourceRow>> {
+        let state = self.state.lock().unwrap();
+        let mut rows: Vec<SourceRow> = state
+            .rows
+            .get(&(tenant_id, dataset))
+            .map(|rows| {
+                rows.values()
+                    .filter(|r| r.watermark.updated_at < until && r.watermark > *after)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default();
+        rows.sort_by(|a, b| a.watermark.cmp(&b.watermark));
+        rows.truncate(limit.max(0) as usize);
+        Ok(rows)
+    }
+}
@@ -0,0 +1,786 @@
+use std::sync::Arc;
+use std::time::Duration as StdDuration;
+
+use aws_sdk_s3::config::{BehaviorVersion, Credentials, Region};
+use axum::async_trait;
+use axum::body::Bytes;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::{DateTime, Duration, Utc};
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use openssl::pkey::PKey;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+use sha2::{Digest, Sha256};
+use sirsi_key_vault::secret::SecretManager;
+use sirsi_key_vault::{Credential, KeyVaultError, SecretString};
+use sirsi_object_store::gcs::TokenSource;
+use sirsi_object_store::{GcsConfig, GcsStore, ObjectStore, PutOptions, S3Store, StoreError, StoreResult};
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+use uuid::Uuid;
+
+use super::schema::{ColumnType, SchemaChange, TableSchema};
+use crate::error::{AppError, AppResult};
+
+pub const BIGQUERY_ENDPOINT: &str = "https://bigquery.googleapis.com";
+const GOOGLE_TOKEN_URI: &str = "https://oauth2.googleapis.com/token";
+const GOOGLE_SCOPES: &str = "https://www.googleapis.com/auth/bigquery https://www.googleapis.com/auth/devstorage.read_write";
+// Export files wait here in the staging bucket until the warehouse has loaded them
+const STAGING_PREFIX: &str = "sirsi-exports";
+const POLL_INTERVAL: StdDuration = StdDuration::from_secs(2);
+const LOAD_TIMEOUT: StdDuration = StdDuration::from_secs(30 * 60);
+// Access tokens are refreshed this long before they expire
+const TOKEN_MARGIN_SECS: i64 = 300;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct S3Location {
+    pub bucket: String,
+    #[serde(default)]
+    pub prefix: String,
+    pub region: String,
+    // S3-compatible stores other than AWS; requests use path-style addressing
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    pub access_key_id: Credential,
+    pub secret_access_key: Credential,
+}
+
+// Credentials are accepted in plaintext when a destination is created, moved into key-vault
+// and only ever stored as `vault:` references
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DestinationConfig {
+    // Load jobs from parquet files staged in a GCS bucket
+    #[serde(rename = "bigquery")]
+    BigQuery {
+        project_id: String,
+        dataset: String,
+        #[serde(default)]
+        location: Option<String>,
+        staging_bucket: String,
+        // Service account key JSON
+        service_account: Credential,
+    },
+    // COPY from an external stage the customer created over the staging location
+    Snowflake {
+        // Account identifier, as in `<account>.snowflakecomputing.com`
+        account: String,
+        user: String,
+        #[serde(default)]
+        role: Option<String>,
+        warehouse: String,
+        database: String,
+        schema: String,
+        stage: String,
+        staging: S3Location,
+        // PEM private key registered for `user`'s key-pair authentication
+        private_key: Credential,
+    },
+    // Parquet files dropped under `<prefix>/<table>/`, with the table's schema alongside
+    S3 { location: S3Location },
+}
+
+fn identifier(value: &str, what: &str, dotted: bool) -> AppResult<()> {
+    let valid = !value.is_empty()
+        && value.len() <= 255
+        && value.split('.').all(|part| {
+            part.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+                && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+        })
+        && (dotted || !value.contains('.'));
+    if !valid {
+        return Err(AppError::Validation(format!("{} '{}' is not a valid identifier", what, value)));
+    }
+    Ok(())
+}
+
+fn required(value: &str, what: &str) -> AppResult<()> {
+    if value.trim().is_empty() {
+        return Err(AppError::Validation(format!("{} is required", what)));
+    }
+    Ok(())
+}
+
+impl S3Location {
+    fn validate(&self) -> AppResult<()> {
+        required(&self.bucket, "bucket")?;
+        required(&self.region, "region")
+    }
+}
+
+impl DestinationConfig {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            DestinationConfig::BigQuery { .. } => "bigquery",
+            DestinationConfig::Snowflake { .. } => "snowflake",
+            DestinationConfig::S3 { .. } => "s3",
+        }
+    }
+
+    pub fn validate(&self) -> AppResult<()> {
+        match self {
+            DestinationConfig::BigQuery { project_id, dataset, staging_bucket, .. } => {
+                required(project_id, "project_id")?;
+                identifier(dataset, "Dataset", false)?;
+                required(staging_bucket, "staging_bucket")
+            }
+            DestinationConfig::Snowflake { account, user, warehouse, database, schema, stage, staging, .. } => {
+                let account_valid = !account.is_empty()
+                    && account.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+                if !account_valid {
+                    return Err(AppError::Validation(format!("'{}' is not a Snowflake account identifier", account)));
+                }
+                required(user, "user")?;
+                identifier(warehouse, "Warehouse", false)?;
+                identifier(database, "Database", false)?;
+                identifier(schema, "Schema", false)?;
+                identifier(stage, "Stage", true)?;
+                staging.validate()
+            }
+            DestinationConfig::S3 { location } => location.validate(),
+        }
+    }
+
+    // Every credential with the name its secret is stored under
+    pub fn credentials_mut(&mut self) -> Vec<(&'static str, &mut Credential)> {
+        match self {
+            DestinationConfig::BigQuery { service_account, .. } => vec![("service_account", service_account)],
+            DestinationConfig::Snowflake { staging, private_key, .. } => vec![
+                ("private_key", private_key),
+                ("staging_access_key_id", &mut staging.access_key_id),
+                ("staging_secret_access_key", &mut staging.secret_access_key),
+            ],
+            DestinationConfig::S3 { location } => vec![
+                ("access_key_id", &mut location.access_key_id),
+                ("secret_access_key", &mut location.secret_access_key),
+            ],
+        }
+    }
+}
+
+// A parquet file written for a run, ready to load
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StagedFile {
+    pub key: String,
+    // Where the file is, e.g. `s3://bucket/key`
+    pub url: String,
+    pub rows: u64,
+    pub bytes: u64,
+}
+
+// One destination's way of taking export files. Files are written to `files()` first; tables
+// are created or extended by `prepare` and filled by `load`.
+#[async_trait]
+pub trait Warehouse: Send + Sync {
+    fn files(&self) -> Arc<dyn ObjectStore>;
+    fn file_key(&self, table: &str, run_id: Uuid, at: DateTime<Utc>) -> String;
+    // Runs before every load with the changes since the last shipped schema; must be safe to
+    // repeat, since a failed load leaves the same changes for the next run
+    async fn prepare(&self, table: &str, schema: &TableSchema, changes: &[SchemaChange]) -> AppResult<()>;
+    // Returns once the warehouse has committed the file's rows
+    async fn load(&self, table: &str, schema: &TableSchema, file: &StagedFile, run_id: Uuid) -> AppResult<()>;
+}
+
+#[async_trait]
+pub trait WarehouseConnector: Send + Sync {
+    async fn connect(&self, config: &DestinationConfig) -> AppResult<Arc<dyn Warehouse>>;
+}
+
+fn join_key(prefix: &str, rest: &str) -> String {
+    match prefix.trim_matches('/') {
+        "" => rest.to_string(),
+        prefix => format!("{}/{}", prefix, rest),
+    }
+}
+
+
+fn http_error(what: &str, e: reqwest::Error) -> AppError {
+    AppError::ExternalService(format!("{} failed: {}", what, e))
+}
+
+// Drops parquet files into a bucket for the customer to pick up. The table's current schema is
+// kept next to its files as `_schema.json`.
+pub struct S3ParquetDrop {
+    store: Arc<dyn ObjectStore>,
+    prefix: String,
+}
+
+impl S3ParquetDrop {
+    pub fn new(store: Arc<dyn ObjectStore>, prefix: impl Into<String>) -> Self {
+        Self { store, prefix: prefix.into() }
+    }
+}
+
+#[async_trait]
+impl Warehouse for S3ParquetDrop {
+    fn files(&self) -> Arc<dyn ObjectStore> {
+        self.store.clone()
+    }
+
+    fn file_key(&self, table: &str, run_id: Uuid, at: DateTime<Utc>) -> String {
+        join_key(&self.prefix, &format!("{}/date={}/{}.parquet", table, at.date_naive(), run_id))
+    }
+
+    async fn prepare(&self, table: &str, schema: &TableSchema, _changes: &[SchemaChange]) -> AppResult<()> {
+        let body = serde_json::to_vec_pretty(schema).map_err(|e| AppError::Serialization(e.to_string()))?;
+        let options = PutOptions::new().with_content_type("application/json");
+        self.store
+            .put(&join_key(&self.prefix, &format!("{}/_schema.json", table)), Bytes::from(body), &options)
+            .await
+            .map_err(AppError::from_provider)?;
+        Ok(())
+    }
+
+    async fn load(&self, _table: &str, _schema: &TableSchema, _file: &StagedFile, _run_id: Uuid) -> AppResult<()> {
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+struct ServiceAccountKey {
+    client_email: String,
+    private_key: String,
+    #[serde(default)]
+    token_uri: Option<String>,
+}
+
+// OAuth tokens for a service account key, through the JWT bearer grant
+pub struct ServiceAccountTokens {
+    http: reqwest::Client,
+    client_email: String,
+    private_key: SecretString,
+    token_uri: String,
+    cached: Mutex<Option<(String, DateTime<Utc>)>>,
+}
+
+impl ServiceAccountTokens {
+    pub fn from_json(http: reqwest::Client, key: &SecretString) -> AppResult<Self> {
+        let key: ServiceAccountKey = serde_json::from_str(key.expose())
+            .map_err(|_| AppError::Configuration("The service account credential is not a key JSON file".into()))?;
+        Ok(Self {
+            http,
+            client_email: key.client_email,
+            private_key: SecretString::new(key.private_key),
+            token_uri: key.token_uri.unwrap_or_else(|| GOOGLE_TOKEN_URI.to_string()),
+            cached: Mutex::new(None),
+        })
+    }
+
+    async fn fetch(&self, now: DateTime<Utc>) -> StoreResult<(String, DateTime<Utc>)> {
+        let claims = json!({
+            "iss": self.client_email,
+            "scope": GOOGLE_SCOPES,
+            "aud": self.token_uri,
+            "iat": now.timestamp(),
+            "exp": (now + Duration::hours(1)).timestamp(),
+        });
+        let key = EncodingKey::from_rsa_pem(self.private_key.expose().as_bytes())
+            .map_err(|e| StoreError::AccessDenied(format!("Invalid service account key: {}", e)))?;
+        let assertion = encode(&Header::new(Algorithm::RS256), &claims, &key)
+            .map_err(|e| StoreError::AccessDenied(format!("Failed to sign token request: {}", e)))?;
+        let response = self
+            .http
+            .post(&self.token_uri)
+            .form(&[("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"), ("assertion", assertion.as_str())])
+            .send()
+            .await
+            .map_err(|e| StoreError::Unavailable(format!("Token request failed: {}", e)))?;
+        if !response.status().is_success() {
+            return Err(StoreError::AccessDenied(format!("Token request was refused with {}", response.status())));
+        }
+        let body: JsonValue = response
+            .json()
+            .await
+            .map_err(|e| StoreError::Backend(format!("Unreadable token response: {}", e)))?;
+        let token = body["access_token"]
+            .as_str()
+            .ok_or_else(|| StoreError::Backend("Token response has no access_token".into()))?;
+        let expires_in = body["expires_in"].as_i64().unwrap_or(3600);
+        Ok((token.to_string(), now + Duration::seconds(expires_in)))
+    }
+}
+
+#[async_trait]
+impl TokenSource for ServiceAccountTokens {
+    async fn token(&self) -> StoreResult<Option<String>> {
+        let now = Utc::now();
+        let mut cached = self.cached.lock().await;
+        match &*cached {
+            Some((token, expires_at)) if *expires_at - Duration::seconds(TOKEN_MARGIN_SECS) > now => Ok(Some(token.clone())),
+            _ => {
+                let (token, expires_at) = self.fetch(now).await?;
+                *cached = Some((token.clone(), expires_at));
+                Ok(Some(token))
+            }
+        }
+    }
+}
+
+fn bigquery_type(kind: ColumnType) -> &'static str {
+    match kind {
+        ColumnType::String | ColumnType::Json => "STRING",
+        ColumnType::Int64 => "INT64",
+        ColumnType::Float64 => "FLOAT64",
+        ColumnType::Bool => "BOOL",
+        ColumnType::Timestamp => "TIMESTAMP",
+        ColumnType::Date => "DATE",
+    }
+}
+
+fn bigquery_schema(schema: &TableSchema) -> JsonValue {
+    let fields: Vec<_> = schema
+        .columns
+        .iter()
+        .map(|c| {
+            json!({
+                "name": c.name,
+                "type": bigquery_type(c.kind),
+                "mode": if c.nullable { "NULLABLE" } else { "REQUIRED" },
+            })
+        })
+        .collect();
+    json!({ "fields": fields })
+}
+
+// Loads staged files with BigQuery load jobs. The job creates the table on first load and
+// takes added or relaxed columns along with it, so there is nothing to prepare.
+pub struct BigQueryWarehouse {
+    http: reqwest::Client,
+    endpoint: String,
+    tokens: Arc<dyn TokenSource>,
+    project_id: String,
+    dataset: String,
+    location: Option<String>,
+    staging: Arc<dyn ObjectStore>,
+    staging_bucket: String,
+}
+
+impl BigQueryWarehouse {
+    pub fn new(
+        http: reqwest::Client,
+        tokens: Arc<dyn TokenSource>,
+        project_id: impl Into<String>,
+        dataset: impl Into<String>,
+        staging_bucket: impl Into<String>,
+    ) -> Self {
+        let staging_bucket = staging_bucket.into();
+        Self {
+            staging: Arc::new(GcsStore::new(GcsConfig::new(staging_bucket.clone(), tokens.clone()))),
+            http,
+            endpoint: BIGQUERY_ENDPOINT.to_string(),
+            tokens,
+            project_id: project_id.into(),
+            dataset: dataset.into(),
+            location: None,
+            staging_bucket,
+        }
+    }
+
+    pub fn with_location(mut self, location: Option<String>) -> Self {
+        self.location = location;
+        self
+    }
+
+    async fn request(&self, request: reqwest::RequestBuilder) -> AppResult<reqwest::Response> {
+        let token = self.tokens.token().await.map_err(AppError::from_provider)?;
+        let request = match token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        };
+        request.send().await.map_err(|e| http_error("BigQuery request", e))
+    }
+
+    fn job_url(&self, job_id: &str) -> String {
+        let mut url = format!("{}/bigquery/v2/projects/{}/jobs/{}", self.endpoint, self.project_id, job_id);
+        if let Some(location) = &self.location {
+            url.push_str(&format!("?location={}", location));
+        }
+        url
+    }
+}
+
+#[async_trait]
+impl Warehouse for BigQueryWarehouse {
+    fn files(&self) -> Arc<dyn ObjectStore> {
+        self.staging.clone()
+    }
+
+    fn file_key(&self, table: &str, run_id: Uuid, _at: DateTime<Utc>) -> String {
+        format!("{}/{}/{}/{}.parquet", STAGING_PREFIX, self.dataset, table, run_id)
+    }
+
+    async fn prepare(&self, _table: &str, _schema: &TableSchema, _changes: &[SchemaChange]) -> AppResult<()> {
+        Ok(())
+    }
+
+    async fn load(&self, table: &str, schema: &TableSchema, file: &StagedFile, run_id: Uuid) -> AppResult<()> {
+        // Named after the run, so a retried load finds the job it already started
+        let job_id = format!("sirsi_export_{}", run_id.simple());
+        let job = json!({
+            "jobReference": { "projectId": self.project_id, "jobId": job_id, "location": self.location },
+            "configuration": {
+                "load": {
+                    "sourceUris": [format!("gs://{}/{}", self.staging_bucket, file.key)],
+                    "sourceFormat": "PARQUET",
+                    "destinationTable": { "projectId": self.project_id, "datasetId": self.dataset, "tableId": table },
+                    "schema": bigquery_schema(schema),
+                    "createDisposition": "CREATE_IF_NEEDED",
+                    "writeDisposition": "WRITE_APPEND",
+                    "schemaUpdateOptions": ["ALLOW_FIELD_ADDITION", "ALLOW_FIELD_RELAXATION"],
+                }
+            }
+        });
+        let url = format!("{}/bigquery/v2/projects/{}/jobs", self.endpoint, self.project_id);
+        let response = self.request(self.http.post(url).json(&job)).await?;
+        let status = response.status();
+        if !status.is_success() && status != reqwest::StatusCode::CONFLICT {
+            let body = response.text().await.unwrap_or_default();
+            return Err(AppError::ExternalService(format!("BigQuery refused the load job ({}): {}", status, body)));
+        }
+
+        let deadline = Instant::now() + LOAD_TIMEOUT;
+        loop {
+            let response = self.request(self.http.get(self.job_url(&job_id))).await?;
+            let job: JsonValue = response
+                .error_for_status()
+                .map_err(|e| http_error("BigQuery job lookup", e))?
+                .json()
+                .await
+                .map_err(|e| http_error("BigQuery job lookup", e))?;
+            if job["status"]["state"] == "DONE" {
+                if let Some(error) = job["status"].get("errorResult") {
+                    return Err(AppError::ExternalService(format!(
+                        "BigQuery load job {} failed: {}",
+                        job_id,
+                        error["message"].as_str().unwrap_or("unknown error")
+                    )));
+                }
+                break;
+            }
+            if Instant::now() >= deadline {
+                return Err(AppError::ExternalService(format!("BigQuery load job {} did not finish in time", job_id)));
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+        self.staging.delete(&file.key).await.map_err(AppError::from_provider)
+    }
+}
+
+fn snowflake_type(kind: ColumnType) -> &'static str {
+    match kind {
+        ColumnType::String | ColumnType::Json => "VARCHAR",
+        ColumnType::Int64 => "NUMBER(19,0)",
+        ColumnType::Float64 => "FLOAT",
+        ColumnType::Bool => "BOOLEAN",
+        ColumnType::Timestamp => "TIMESTAMP_TZ",
+        ColumnType::Date => "DATE",
+    }
+}
+
+// Key-pair JWT for the SQL API; the issuer names the public key by its SHA-256 fingerprint
+fn snowflake_jwt(account: &str, user: &str, private_key: &SecretString, now: DateTime<Utc>) -> AppResult<String> {
+    let invalid = |e: &dyn std::fmt::Display| AppError::Configuration(format!("Invalid Snowflake private key: {}", e));
+    let key = PKey::private_key_from_pem(private_key.expose().as_bytes()).map_err(|e| invalid(&e))?;
+    let public = key.public_key_to_der().map_err(|e| invalid(&e))?;
+    let fingerprint = BASE64.encode(Sha256::digest(&public));
+    // Account locators with a region suffix are identified by the locator alone
+    let account = account.split('.').next().unwrap_or(account).to_uppercase();
+    let qualified = format!("{}.{}", account, user.to_uppercase());
+    let claims = json!({
+        "iss": format!("{}.SHA256:{}", qualified, fingerprint),
+        "sub": qualified,
+        "iat": now.timestamp(),
+        "exp": (now + Duration::minutes(59)).timestamp(),
+    });
+    let key = EncodingKey::from_rsa_pem(private_key.expose().as_bytes()).map_err(|e| invalid(&e))?;
+    encode(&Header::new(Algorithm::RS256), &claims, &key).map_err(|e| invalid(&e))
+}
+
+// Copies staged files into Snowflake through the SQL API. Snowflake remembers which staged
+// files a table has loaded, so repeating a COPY for the same file loads nothing twice.
+pub struct SnowflakeWarehouse {
+    http: reqwest::Client,
+    endpoint: String,
+    account: String,
+    user: String,
+    role: Option<String>,
+    warehouse: String,
+    database: String,
+    schema: String,
+    stage: String,
+    private_key: SecretString,
+    staging: Arc<dyn ObjectStore>,
+    staging_prefix: String,
+}
+
+impl SnowflakeWarehouse {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        http: reqwest::Client,
+        account: impl Into<String>,
+        user: impl Into<String>,
+        warehouse: impl Into<String>,
+        database: impl Into<String>,
+        schema: impl Into<String>,
+        stage: impl Into<String>,
+        private_key: SecretString,
+        staging: Arc<dyn ObjectStore>,
+        staging_prefix: impl Into<String>,
+    ) -> Self {
+        let account = account.into();
+        Self {
+            http,
+            endpoint: format!("https://{}.snowflakecomputing.com", account),
+            account,
+            user: user.into(),
+            role: None,
+            warehouse: warehouse.into(),
+            database: database.into(),
+            schema: schema.into(),
+            stage: stage.into(),
+            private_key,
+            staging,
+            staging_prefix: staging_prefix.into(),
+        }
+    }
+
+    pub fn with_role(mut self, role: Option<String>) -> Self {
+        self.role = role;
+        self
+    }
+
+    async fn execute(&self, statement: &str) -> AppResult<()> {
+        let jwt = snowflake_jwt(&self.account, &self.user, &self.private_key, Utc::now())?;
+        let authorized = |request: reqwest::RequestBuilder| {
+            request
+                .bearer_auth(&jwt)
+                .header("X-Snowflake-Authorization-Token-Type", "KEYPAIR_JWT")
+                .header("Accept", "application/json")
+        };
+        let body = json!({
+            "statement": statement,
+            "timeout": LOAD_TIMEOUT.as_secs(),
+            "warehouse": self.warehouse,
+            "database": self.database,
+            "schema": self.schema,
+            "role": self.role,
+        });
+        let mut response = authorized(self.http.post(format!("{}/api/v2/statements", self.endpoint)).json(&body))
+            .send()
+            .await
+            .map_err(|e| http_error("Snowflake statement", e))?;
+        let deadline = Instant::now() + LOAD_TIMEOUT;
+        // 202 means the statement is still running; its handle is polled until it finishes
+        while response.status() == reqwest::StatusCode::ACCEPTED {
+            let pending: JsonValue = response.json().await.map_err(|e| http_error("Snowflake statement", e))?;
+            let handle = pending["statementHandle"]
+                .as_str()
+                .ok_or_else(|| AppError::ExternalService("Snowflake returned no statement handle".into()))?
+                .to_string();
+            if Instant::now() >= deadline {
+                return Err(AppError::ExternalService(format!("Snowflake statement {} did not finish in time", handle)));
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+            response = authorized(self.http.get(format!("{}/api/v2/statements/{}", self.endpoint, handle)))
+                .send()
+                .await
+                .map_err(|e| http_error("Snowflake statement", e))?;
+        }
+        if !response.status().is_success() {
+            let status = response.status();
+            let body: JsonValue = response.json().await.unwrap_or_default();
+            return Err(AppError::ExternalService(format!(
+                "Snowflake statement failed ({}): {}",
+                status,
+                body["message"].as_str().unwrap_or("no message")
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Warehouse for SnowflakeWarehouse {
+    fn files(&self) -> Arc<dyn ObjectStore> {
+        self.staging.clone()
+    }
+
+    fn file_key(&self, table: &str, run_id: Uuid, _at: DateTime<Utc>) -> String {
+        join_key(&self.staging_prefix, &format!("{}/{}.parquet", table, run_id))
+    }
+
+    async fn prepare(&self, table: &str, schema: &TableSchema, changes: &[SchemaChange]) -> AppResult<()> {
+        let columns: Vec<_> = schema
+            .columns
+            .iter()
+            .map(|c| format!("{} {}{}", c.name, snowflake_type(c.kind), if c.nullable { "" } else { " NOT NULL" }))
+            .collect();
+        self.execute(&format!("CREATE TABLE IF NOT EXISTS {} ({})", table, columns.join(", "))).await?;
+        for change in changes {
+            match change {
+                SchemaChange::Added { column } => {
+                    self.execute(&format!(
+                        "ALTER TABLE {} ADD COLUMN IF NOT EXISTS {} {}",
+                        table,
+                        column.name,
+                        snowflake_type(column.kind)
+                    ))
+                    .await?
+                }
+                SchemaChange::Relaxed { name } => {
+                    self.execute(&format!("ALTER TABLE {} ALTER COLUMN {} DROP NOT NULL", table, name)).await?
+                }
+                // Breaking changes never reach a load
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    async fn load(&self, table: &str, _schema: &TableSchema, file: &StagedFile, _run_id: Uuid) -> AppResult<()> {
+        let path = file.key.strip_prefix(self.staging_prefix.trim_matches('/')).unwrap_or(&file.key).trim_start_matches('/');
+        self.execute(&format!(
+            "COPY INTO {} FROM @{}/{} FILE_FORMAT = (TYPE = PARQUET USE_LOGICAL_TYPE = TRUE) \
+            MATCH_BY_COLUMN_NAME = CASE_INSENSITIVE ON_ERROR = ABORT_STATEMENT",
+            table, self.stage, path
+        ))
+        .await?;
+        self.staging.delete(&file.key).await.map_err(AppError::from_provider)
+    }
+}
+
+fn credential_error(e: KeyVaultError) -> AppError {
+    AppError::Configuration(format!("Destination credentials are unavailable: {}", e))
+}
+
+// Builds warehouses from stored destination configs, resolving credentials from key-vault on
+// every connect so rotated secrets are picked up by the next run
+pub struct CloudWarehouseConnector {
+    secrets: Arc<dyn SecretManager>,
+    http: reqwest::Client,
+}
+
+impl CloudWarehouseConnector {
+    pub fn new(secrets: Arc<dyn SecretManager>) -> Self {
+        let http = reqwest::Client::builder()
+            .timeout(StdDuration::from_secs(60))
+            .user_agent("sirsi-warehouse-export")
+            .build()
+            .unwrap_or_default();
+        Self { secrets, http }
+    }
+
+    async fn resolve(&self, credential: &Credential) -> AppResult<SecretString> {
+        credential.resolve(Some(self.secrets.as_ref())).await.map_err(credential_error)
+    }
+
+    async fn s3_store(&self, location: &S3Location) -> AppResult<Arc<dyn ObjectStore>> {
+        let access_key_id = self.resolve(&location.access_key_id).await?;
+        let secret_access_key = self.resolve(&location.secret_access_key).await?;
+        let mut config = aws_sdk_s3::config::Builder::new()
+            .behavior_version(BehaviorVersion::latest())
+            .region(Region::new(location.region.clone()))
+            .credentials_provider(Credentials::new(
+                access_key_id.expose(),
+                secret_access_key.expose(),
+                None,
+                None,
+                "sirsi-warehouse-export",
+            ));
+        if let Some(endpoint) = &location.endpoint {
+            config = config.endpoint_url(endpoint).force_path_style(true);
+        }
+        Ok(Arc::new(S3Store::new(aws_sdk_s3::Client::from_conf(config.build()), location.bucket.clone())))
+    }
+}
+
+#[async_trait]
+impl WarehouseConnector for CloudWarehouseConnector {
+    async fn connect(&self, config: &DestinationConfig) -> AppResult<Arc<dyn Warehouse>> {
+        Ok(match config {
+            DestinationConfig::BigQuery { project_id, dataset, location, staging_bucket, service_account } => {
+                let key = self.resolve(service_account).await?;
+                let tokens = Arc::new(ServiceAccountTokens::from_json(self.http.clone(), &key)?);
+                Arc::new(
+                    BigQueryWarehouse::new(self.http.clone(), tokens, project_id, dataset, staging_bucket)
+                        .with_location(location.clone()),
+                )
+            }
+            DestinationConfig::Snowflake { account, user, role, warehouse, database, schema, stage, staging, private_key } => {
+                Arc::new(
+                    SnowflakeWarehouse::new(
+                        self.http.clone(),
+                        account,
+                        user,
+                        warehouse,
+                        database,
+                        schema,
+                        stage,
+                        self.resolve(private_key).await?,
+                        self.s3_store(staging).await?,
+                        staging.prefix.clone(),
+                    )
+                    .with_role(role.clone()),
+                )
+            }
+            DestinationConfig::S3 { location } => {
+                Arc::new(S3ParquetDrop::new(self.s3_store(location).await?, location.prefix.clone()))
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::warehouse::schema::Column;
+
+    #[test]
+    fn test_config_parses_and_validates() {
+        let config: DestinationConfig = serde_json::from_value(json!({
+            "type": "snowflake",
+            "account": "xy12345.us-east-1",
+            "user": "exporter",
+            "warehouse": "LOAD_WH",
+            "database": "ANALYTICS",
+            "schema": "SIRSI",
+            "stage": "ANALYTICS.SIRSI.EXPORTS",
+            "staging": {
+                "bucket": "acme-stage",
+                "prefix": "sirsi/",
+                "region": "us-east-1",
+                "access_key_id": "vault:warehouse/t/d/staging_access_key_id",
+                "secret_access_key": "vault:warehouse/t/d/staging_secret_access_key"
+            },
+            "private_key": "vault:warehouse/t/d/private_key"
+        }))
+        .unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.kind(), "snowflake");
+
+        let bad: DestinationConfig = serde_json::from_value(json!({
+            "type": "bigquery",
+            "project_id": "acme",
+            "dataset": "sirsi; DROP TABLE x",
+            "staging_bucket": "acme-stage",
+            "service_account": "{}"
+        }))
+        .unwrap();
+        assert!(matches!(bad.validate(), Err(AppError::Validation(_))));
+        // Plaintext credentials can't be stored until they are moved into key-vault
+        assert!(serde_json::to_string(&bad).is_err());
+
+        let schema = TableSchema::new(vec![
+            Column::required("id", ColumnType::String),
+            Column::nullable("finished_at", ColumnType::Timestamp),
+        ]);
+        assert_eq!(
+            bigquery_schema(&schema),
+            json!({ "fields": [
+                { "name": "id", "type": "STRING", "mode": "REQUIRED" },
+                { "name": "finished_at", "type": "TIMESTAMP", "mode": "NULLABLE" },
+            ]})
+        );
+    }
+}
@@ -0,0 +1,714 @@
+use std::sync::Arc;
+use std::time::Duration as StdDuration;
+
+use axum::async_trait;
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use sirsi_key_vault::secret::SecretManager;
+use sirsi_key_vault::KeyVaultError;
+use sirsi_object_store::{PutOptions, StoreError, StoreResult, Uploader};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::error::{AppError, AppResult};
+
+pub mod datasets;
+pub mod destinations;
+pub mod parquet;
+pub mod schema;
+pub mod store;
+
+pub use datasets::{Dataset, DatasetSource, InMemoryDatasetSource, PgDatasetSource, SourceRow, Watermark};
+pub use destinations::{CloudWarehouseConnector, DestinationConfig, StagedFile, Warehouse, WarehouseConnector};
+pub use schema::{Column, ColumnType, SchemaChange, TableSchema};
+pub use store::{InMemoryWarehouseStore, PgWarehouseStore};
+
+use self::parquet::ParquetWriter;
+
+const DEFAULT_BATCH_SIZE: i64 = 10_000;
+// Rows are only read once they are this old: `updated_at` is the writing transaction's
+// timestamp, so a long transaction can commit rows older than ones already shipped
+const SETTLE_MINUTES: i64 = 5;
+// How long a run may hold its export before another run can take over; covers the slowest load
+const LEASE_MINUTES: i64 = 60;
+const MIN_INTERVAL_MINUTES: i64 = 15;
+const MAX_INTERVAL_MINUTES: i64 = 7 * 24 * 60;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Destination {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub name: String,
+    pub config: DestinationConfig,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewDestination {
+    pub name: String,
+    // Credentials may be given in plaintext; they are moved into key-vault before anything is stored
+    pub config: DestinationConfig,
+}
+
+// What an export has shipped so far
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ExportProgress {
+    pub watermark: Watermark,
+    // The schema of the last load; None until the first rows ship
+    pub schema: Option<TableSchema>,
+    // Breaking changes the export is stopped on until they are accepted
+    pub blocked_changes: Vec<SchemaChange>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportDefinition {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub destination_id: Uuid,
+    pub dataset: Dataset,
+    // Destination table, the dataset's name unless given
+    pub table: String,
+    // Runs at every multiple of the interval since the epoch, so hourly exports run on the hour
+    pub interval_minutes: i64,
+    pub created_at: DateTime<Utc>,
+    pub last_scheduled_for: Option<DateTime<Utc>>,
+    #[serde(flatten)]
+    pub progress: ExportProgress,
+}
+
+impl ExportDefinition {
+    // The slot to run for, if one has passed since the last scheduled run
+    pub fn due_slot(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let interval = self.interval_minutes.max(1) * 60;
+        let slot = Utc.timestamp_opt(now.timestamp().div_euclid(interval) * interval, 0).single()?;
+        (slot > self.last_scheduled_for.unwrap_or(self.created_at)).then_some(slot)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewExport {
+    pub destination_id: Uuid,
+    pub dataset: Dataset,
+    #[serde(default)]
+    pub table: Option<String>,
+    pub interval_minutes: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "VARCHAR", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum RunTrigger {
+    Scheduled,
+    Manual,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "VARCHAR", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ExportRunStatus {
+    Running,
+    Succeeded,
+    Failed,
+    // Stopped on a breaking schema change; nothing was shipped
+    Blocked,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportRun {
+    pub id: Uuid,
+    pub export_id: Uuid,
+    pub tenant_id: Uuid,
+    pub trigger: RunTrigger,
+    pub status: ExportRunStatus,
+    pub rows: u64,
+    pub bytes: u64,
+    // Where the run's parquet file was written
+    pub file: Option<String>,
+    pub watermark_from: Watermark,
+    // Set once the run's rows are loaded
+    pub watermark_to: Option<Watermark>,
+    // Changes since the previous load: propagated on success, the reason for a blocked run
+    pub schema_changes: Vec<SchemaChange>,
+    pub error: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+#[async_trait]
+pub trait WarehouseStore: Send + Sync {
+    async fn create_destination(&self, destination: &Destination) -> AppResult<()>;
+    async fn get_destination(&self, tenant_id: Uuid, id: Uuid) -> AppResult<Option<Destination>>;
+    async fn list_destinations(&self, tenant_id: Uuid) -> AppResult<Vec<Destination>>;
+    async fn delete_destination(&self, tenant_id: Uuid, id: Uuid) -> AppResult<bool>;
+    async fn create_export(&self, export: &ExportDefinition) -> AppResult<()>;
+    async fn get_export(&self, tenant_id: Uuid, id: Uuid) -> AppResult<Option<ExportDefinition>>;
+    // All tenants when `tenant_id` is `None`
+    async fn list_exports(&self, tenant_id: Option<Uuid>) -> AppResult<Vec<ExportDefinition>>;
+    async fn delete_export(&self, tenant_id: Uuid, id: Uuid) -> AppResult<bool>;
+    // Moves `last_scheduled_for` from `previous` to `slot`; false when another scheduler got there first
+    async fn claim_slot(&self, id: Uuid, previous: Option<DateTime<Utc>>, slot: DateTime<Utc>) -> AppResult<bool>;
+    // Holds the export until `until`; false while another run's lease is unexpired
+    async fn acquire_lease(&self, id: Uuid, now: DateTime<Utc>, until: DateTime<Utc>) -> AppResult<bool>;
+    async fn create_run(&self, run: &ExportRun) -> AppResult<()>;
+    // Saves a finished run together with the export's progress and releases the lease
+    async fn finish_run(&self, run: &ExportRun, progress: &ExportProgress) -> AppResult<()>;
+    async fn save_progress(&self, tenant_id: Uuid, id: Uuid, progress: &ExportProgress) -> AppResult<bool>;
+    // Newest first
+    async fn list_runs(&self, tenant_id: Uuid, export_id: Uuid, limit: i64) -> AppResult<Vec<ExportRun>>;
+}
+
+fn vault_error(e: KeyVaultError) -> AppError {
+    match e {
+        KeyVaultError::Validation(msg) => AppError::Validation(msg),
+        KeyVaultError::Permission(msg) => AppError::Forbidden(msg),
+        KeyVaultError::NotFound(msg) => AppError::NotFound(msg),
+        e => AppError::Internal(format!("Storing destination credentials failed: {}", e)),
+    }
+}
+
+fn secret_prefix(tenant_id: Uuid, destination_id: Uuid) -> String {
+    format!("warehouse/{}/{}", tenant_id, destination_id)
+}
+
+// Ships datasets to customer warehouses. Each run reads the rows changed since the export's
+// watermark, streams them into one parquet file and loads it; the watermark only moves once the
+// load has succeeded, so delivery is at least once and rows carry their key and `updated_at` for
+// deduplication downstream. Deleted rows are not exported.
+pub struct WarehouseService {
+    store: Arc<dyn WarehouseStore>,
+    datasets: Arc<dyn DatasetSource>,
+    secrets: Arc<dyn SecretManager>,
+    connector: Arc<dyn WarehouseConnector>,
+    batch_size: i64,
+}
+
+impl WarehouseService {
+    pub fn new(store: Arc<dyn WarehouseStore>, datasets: Arc<dyn DatasetSource>, secrets: Arc<dyn SecretManager>) -> Self {
+        Self {
+            store,
+            datasets,
+            connector: Arc::new(CloudWarehouseConnector::new(secrets.clone())),
+            secrets,
+            batch_size: DEFAULT_BATCH_SIZE,
+        }
+    }
+
+    pub fn with_connector(mut self, connector: Arc<dyn WarehouseConnector>) -> Self {
+        self.connector = connector;
+        self
+    }
+
+    // Rows per read and per parquet row group
+    pub fn with_batch_size(mut self, batch_size: i64) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    pub fn schema(&self, dataset: Dataset) -> TableSchema {
+        self.datasets.schema(dataset)
+    }
+
+    pub async fn create_destination(
+        &self,
+        tenant_id: Uuid,
+        destination: NewDestination,
+        now: DateTime<Utc>,
+    ) -> AppResult<Destination> {
+        if destination.name.trim().is_empty() {
+            return Err(AppError::Validation("Destination name is required".into()));
+        }
+        let mut config = destination.config;
+        config.validate()?;
+        let id = Uuid::new_v4();
+        let prefix = secret_prefix(tenant_id, id);
+        for (field, credential) in config.credentials_mut() {
+            // Only secrets this destination owns can be referenced, not another tenant's
+            if let Some(reference) = credential.reference() {
+                if !reference.secret_id.starts_with(&format!("{}/", prefix)) {
+                    return Err(AppError::Validation(format!("{} must be given in plaintext", field)));
+                }
+            }
+            credential.ingest(self.secrets.as_ref(), &format!("{}/{}", prefix, field)).await.map_err(vault_error)?;
+        }
+        let destination = Destination { id, tenant_id, name: destination.name, config, created_at: now };
+        self.store.create_destination(&destination).await?;
+        Ok(destination)
+    }
+
+    pub async fn destinations(&self, tenant_id: Uuid) -> AppResult<Vec<Destination>> {
+        self.store.list_destinations(tenant_id).await
+    }
+
+    pub async fn destination(&self, tenant_id: Uuid, id: Uuid) -> AppResult<Destination> {
+        self.store
+            .get_destination(tenant_id, id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Destination not found".into()))
+    }
+
+    pub async fn delete_destination(&self, tenant_id: Uuid, id: Uuid) -> AppResult<()> {
+        let mut destination = self.destination(tenant_id, id).await?;
+        if self.store.list_exports(Some(tenant_id)).await?.iter().any(|e| e.destination_id == id) {
+            return Err(AppError::Conflict { message: "Delete the destination's exports first".into(), current: None });
+        }
+        if !self.store.delete_destination(tenant_id, id).await? {
+            return Err(AppError::NotFound("Destination not found".into()));
+        }
+        for (_, credential) in destination.config.credentials_mut() {
+            if let Some(reference) = credential.reference() {
+                if let Err(e) = self.secrets.delete_secret(&reference.secret_id).await {
+                    warn!("Failed to delete warehouse credential {}: {}", reference.secret_id, e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn create_export(&self, tenant_id: Uuid, export: NewExport, now: DateTime<Utc>) -> AppResult<ExportDefinition> {
+        self.destination(tenant_id, export.destination_id).await?;
+        if !(MIN_INTERVAL_MINUTES..=MAX_INTERVAL_MINUTES).contains(&export.interval_minutes) {
+            return Err(AppError::Validation(format!(
+                "Interval must be between {} and {} minutes",
+                MIN_INTERVAL_MINUTES, MAX_INTERVAL_MINUTES
+            )));
+        }
+        let table = export.table.unwrap_or_else(|| export.dataset.as_str().to_string());
+        let valid = table.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && table.len() <= 128
+            && table.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid {
+            return Err(AppError::Validation(format!("'{}' is not a valid table name", table)));
+        }
+        let definition = ExportDefinition {
+            id: Uuid::new_v4(),
+            tenant_id,
+            destination_id: export.destination_id,
+            dataset: export.dataset,
+            table,
+            interval_minutes: export.interval_minutes,
+            created_at: now,
+            last_scheduled_for: None,
+            progress: ExportProgress::default(),
+        };
+        self.store.create_export(&definition).await?;
+        Ok(definition)
+    }
+
+    pub async fn exports(&self, tenant_id: Uuid) -> AppResult<Vec<ExportDefinition>> {
+        self.store.list_exports(Some(tenant_id)).await
+    }
+
+    pub async fn export(&self, tenant_id: Uuid, id: Uuid) -> AppResult<ExportDefinition> {
+        self.store
+            .get_export(tenant_id, id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Export not found".into()))
+    }
+
+    pub async fn delete_export(&self, tenant_id: Uuid, id: Uuid) -> AppResult<()> {
+        if !self.store.delete_export(tenant_id, id).await? {
+            return Err(AppError::NotFound("Export not found".into()));
+        }
+        Ok(())
+    }
+
+    // Leaves the schedule untouched
+    pub async fn run_now(&self, tenant_id: Uuid, id: Uuid, now: DateTime<Utc>) -> AppResult<ExportRun> {
+        let export = self.export(tenant_id, id).await?;
+        self.execute(&export, RunTrigger::Manual, now).await
+    }
+
+    pub async fn run_due(&self, now: DateTime<Utc>) -> AppResult<Vec<ExportRun>> {
+        let mut runs = Vec::new();
+        for export in self.store.list_exports(None).await? {
+            let Some(slot) = export.due_slot(now) else {
+                continue;
+            };
+            if !self.store.claim_slot(export.id, export.last_scheduled_for, slot).await? {
+                continue;
+            }
+            match self.execute(&export, RunTrigger::Scheduled, now).await {
+                Ok(run) => runs.push(run),
+                Err(e) => error!("Scheduled export {} failed: {}", export.id, e),
+            }
+        }
+        Ok(runs)
+    }
+
+    pub fn spawn_scheduler(self: Arc<Self>, interval: StdDuration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                match self.run_due(Utc::now()).await {
+                    Ok(runs) if !runs.is_empty() => info!("Ran {} scheduled warehouse exports", runs.len()),
+                    Ok(_) => {}
+                    Err(e) => warn!("Failed to check for due warehouse exports: {}", e),
+                }
+            }
+        })
+    }
+
+    pub async fn runs(&self, tenant_id: Uuid, export_id: Uuid, limit: i64) -> AppResult<Vec<ExportRun>> {
+        self.export(tenant_id, export_id).await?;
+        self.store.list_runs(tenant_id, export_id, limit).await
+    }
+
+    // Unblocks an export once the destination table has been changed to match the dataset's
+    // current schema. `restart` ships everything again, e.g. after the table was recreated.
+    pub async fn accept_schema(&self, tenant_id: Uuid, id: Uuid, restart: bool) -> AppResult<ExportDefinition> {
+        let mut export = self.export(tenant_id, id).await?;
+        export.progress.schema = Some(self.datasets.schema(export.dataset));
+        export.progress.blocked_changes.clear();
+        if restart {
+            export.progress.watermark = Watermark::default();
+        }
+        if !self.store.save_progress(tenant_id, id, &export.progress).await? {
+            return Err(AppError::NotFound("Export not found".into()));
+        }
+        Ok(export)
+    }
+
+    // A failed run is stored as such rather than returned as an error
+    async fn execute(&self, export: &ExportDefinition, trigger: RunTrigger, now: DateTime<Utc>) -> AppResult<ExportRun> {
+        if !self.store.acquire_lease(export.id, now, now + Duration::minutes(LEASE_MINUTES)).await? {
+            return Err(AppError::Conflict { message: "The export is already running".into(), current: None });
+        }
+        let mut run = ExportRun {
+            id: Uuid::new_v4(),
+            export_id: export.id,
+            tenant_id: export.tenant_id,
+            trigger,
+            status: ExportRunStatus::Running,
+            rows: 0,
+            bytes: 0,
+            file: None,
+            watermark_from: export.progress.watermark.clone(),
+            watermark_to: None,
+            schema_changes: Vec::new(),
+            error: None,
+            started_at: now,
+            finished_at: None,
+        };
+        self.store.create_run(&run).await?;
+
+        let mut progress = export.progress.clone();
+        if let Err(e) = self.ship(export, &mut run, &mut progress, now).await {
+            warn!("Warehouse export {} failed: {}", export.id, e);
+            run.status = ExportRunStatus::Failed;
+            run.error = Some(e.to_string());
+        }
+        run.finished_at = Some(Utc::now());
+        self.store.finish_run(&run, &progress).await?;
+        Ok(run)
+    }
+
+    async fn ship(
+        &self,
+        export: &ExportDefinition,
+        run: &mut ExportRun,
+        progress: &mut ExportProgress,
+        now: DateTime<Utc>,
+    ) -> AppResult<()> {
+        let schema = self.datasets.schema(export.dataset);
+        run.schema_changes = progress.schema.as_ref().map(|shipped| schema.changes_since(shipped)).unwrap_or_default();
+        let breaking: Vec<_> = run.schema_changes.iter().filter(|c| c.is_breaking()).cloned().collect();
+        if !breaking.is_empty() {
+            run.status = ExportRunStatus::Blocked;
+            run.error = Some("The dataset's schema changed in a way the destination table can't follow".into());
+            progress.blocked_changes = breaking;
+            return Ok(());
+        }
+
+        let until = now - Duration::minutes(SETTLE_MINUTES);
+        let first = self
+            .datasets
+            .read(export.tenant_id, export.dataset, &progress.watermark, until, self.batch_size)
+            .await?;
+        if first.is_empty() {
+            run.status = ExportRunStatus::Succeeded;
+            return Ok(());
+        }
+
+        let destination = self.destination(export.tenant_id, export.destination_id).await?;
+        let warehouse = self.connector.connect(&destination.config).await?;
+        warehouse.prepare(&export.table, &schema, &run.schema_changes).await?;
+
+        let key = warehouse.file_key(&export.table, run.id, now);
+        let (tx, rx) = mpsc::channel(2);
+        let producer = tokio::spawn(produce(
+            self.datasets.clone(),
+            export.clone(),
+            schema.clone(),
+            first,
+            until,
+            self.batch_size,
+            tx,
+        ));
+        let options = PutOptions::new().with_content_type(parquet::CONTENT_TYPE);
+        let uploaded = Uploader::new(warehouse.files()).upload(&key, ReceiverStream::new(rx).boxed(), &options).await;
+        let produced = producer
+            .await
+            .map_err(|e| AppError::Internal(format!("Export writer stopped: {}", e)))??;
+        let meta = uploaded.map_err(AppError::from_provider)?;
+        let Some((rows, watermark)) = produced else {
+            return Err(AppError::Internal("The upload stopped before the export file was written".into()));
+        };
+
+        let file = StagedFile { url: warehouse.files().url(&key), key, rows, bytes: meta.size };
+        run.file = Some(file.url.clone());
+        run.rows = rows;
+        run.bytes = file.bytes;
+        warehouse.load(&export.table, &schema, &file, run.id).await?;
+
+        run.status = ExportRunStatus::Succeeded;
+        run.watermark_to = Some(watermark.clone());
+        *progress = ExportProgress { watermark, schema: Some(schema), blocked_changes: Vec::new() };
+        Ok(())
+    }
+}
+
+// Writes one row group per batch into the upload, reading batches until the source runs dry.
+// Returns the row count and last watermark, or None if the upload stopped taking data.
+async fn produce(
+    datasets: Arc<dyn DatasetSource>,
+    export: ExportDefinition,
+    schema: TableSchema,
+    first: Vec<SourceRow>,
+    until: DateTime<Utc>,
+    batch_size: i64,
+    tx: mpsc::Sender<StoreResult<axum::body::Bytes>>,
+) -> AppResult<Option<(u64, Watermark)>> {
+    let result = async {
+        let mut writer = ParquetWriter::new(&schema)?;
+        let mut batch = first;
+        let mut last = Watermark::default();
+        while let Some(tail) = batch.last() {
+            last = tail.watermark.clone();
+            let full = batch.len() as i64 >= batch_size;
+            let rows: Vec<_> = batch.into_iter().map(|r| r.values).collect();
+            if tx.send(Ok(writer.write_row_group(&rows)?)).await.is_err() {
+                return Ok(None);
+            }
+            if !full {
+                break;
+            }
+            batch = datasets.read(export.tenant_id, export.dataset, &last, until, batch_size).await?;
+        }
+        let rows = writer.rows();
+        if tx.send(Ok(writer.finish()?)).await.is_err() {
+            return Ok(None);
+        }
+        Ok(Some((rows, last)))
+    }
+    .await;
+    // Without an error the upload would complete with whatever was written so far
+    if let Err(e) = &result {
+        let _ = tx.send(Err(StoreError::Backend(e.to_string()))).await;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::warehouse::destinations::S3ParquetDrop;
+    use crate::warehouse::schema::Value;
+    use ::parquet::basic::Repetition;
+    use ::parquet::file::reader::{FileReader, SerializedFileReader};
+    use ::parquet::record::RowAccessor;
+    use chrono::NaiveDate;
+    use serde_json::json;
+    use sirsi_key_vault::secret::InMemorySecretManager;
+    use sirsi_object_store::{LocalFileStore, ObjectStore};
+
+    // Every destination is a parquet drop into the same local bucket
+    struct LocalBucket(Arc<dyn ObjectStore>);
+
+    #[async_trait]
+    impl WarehouseConnector for LocalBucket {
+        async fn connect(&self, config: &DestinationConfig) -> AppResult<Arc<dyn Warehouse>> {
+            let DestinationConfig::S3 { location } = config else {
+                return Err(AppError::Configuration("Only S3 destinations are available".into()));
+            };
+            Ok(Arc::new(S3ParquetDrop::new(self.0.clone(), location.prefix.clone())))
+        }
+    }
+
+    fn metric(day: u32, metric: &str, count: i64, updated_at: DateTime<Utc>) -> SourceRow {
+        let day = NaiveDate::from_ymd_opt(2025, 7, day).unwrap();
+        SourceRow {
+            values: vec![day.into(), metric.into(), count.into(), updated_at.into()],
+            watermark: Watermark { updated_at, key: format!("{}/{}", day, metric) },
+        }
+    }
+
+    fn file_key(run: &ExportRun) -> String {
+        format!("exports/daily_metrics/date={}/{}.parquet", run.started_at.date_naive(), run.id)
+    }
+
+    async fn object(store: &dyn ObjectStore, key: &str) -> Vec<u8> {
+        let (_, mut stream) = store.get(key).await.unwrap();
+        let mut body = Vec::new();
+        while let Some(part) = stream.next().await {
+            body.extend_from_slice(&part.unwrap());
+        }
+        body
+    }
+
+    // (metric, count) pairs in file order
+    fn metrics(file: Vec<u8>) -> Vec<(String, i64)> {
+        let reader = SerializedFileReader::new(axum::body::Bytes::from(file)).unwrap();
+        reader
+            .get_row_iter(None)
+            .unwrap()
+            .map(|row| {
+                let row = row.unwrap();
+                (row.get_string(1).unwrap().clone(), row.get_long(2).unwrap())
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_incremental_runs_follow_watermarks_and_schema_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let bucket: Arc<dyn ObjectStore> = Arc::new(LocalFileStore::new(dir.path()));
+        let source = Arc::new(InMemoryDatasetSource::new());
+        let secrets = Arc::new(InMemorySecretManager::new());
+        let service = WarehouseService::new(Arc::new(InMemoryWarehouseStore::new()), source.clone(), secrets.clone())
+            .with_connector(Arc::new(LocalBucket(bucket.clone())))
+            .with_batch_size(2);
+        let tenant = Uuid::new_v4();
+        let start = Utc.with_ymd_and_hms(2025, 7, 25, 9, 0, 0).unwrap();
+
+        let config: DestinationConfig = serde_json::from_value(json!({
+            "type": "s3",
+            "location": {
+                "bucket": "acme-lake",
+                "prefix": "exports",
+                "region": "us-east-1",
+                "access_key_id": "AKIAEXAMPLE",
+                "secret_access_key": "wJalrXUtnFEMI"
+            }
+        }))
+        .unwrap();
+        let destination = service
+            .create_destination(tenant, NewDestination { name: "lake".into(), config }, start)
+            .await
+            .unwrap();
+        // Credentials only leave the request as key-vault references
+        let DestinationConfig::S3 { location } = &destination.config else { panic!("expected an S3 destination") };
+        let secret_id = format!("warehouse/{}/{}/secret_access_key", tenant, destination.id);
+        assert_eq!(location.secret_access_key.reference().map(|r| r.secret_id.as_str()), Some(secret_id.as_str()));
+        assert!(secrets.get_secret(&secret_id).await.is_ok());
+
+        let export = service
+            .create_export(
+                tenant,
+                NewExport {
+                    destination_id: destination.id,
+                    dataset: Dataset::DailyMetrics,
+                    table: None,
+                    interval_minutes: 60,
+                },
+                start,
+            )
+            .await
+            .unwrap();
+
+        // Three rows take two row groups; the last one is too recent to be read yet
+        let t = |minutes: i64| start + Duration::minutes(minutes);
+        for (i, name) in ["workflow_run", "api_call", "discovery_run"].into_iter().enumerate() {
+            source.put(tenant, Dataset::DailyMetrics, metric(24, name, 10 + i as i64, t(i as i64)));
+        }
+        source.put(tenant, Dataset::DailyMetrics, metric(25, "api_call", 1, t(58)));
+        let first = service.run_now(tenant, export.id, t(60)).await.unwrap();
+        assert_eq!(first.status, ExportRunStatus::Succeeded);
+        assert_eq!(first.rows, 3);
+        assert_eq!(first.watermark_from, Watermark::default());
+        assert_eq!(first.watermark_to.as_ref().unwrap().updated_at, t(2));
+        assert_eq!(first.file, Some(bucket.url(&file_key(&first))));
+        let file = object(bucket.as_ref(), &file_key(&first)).await;
+        let reader = SerializedFileReader::new(axum::body::Bytes::from(file.clone())).unwrap();
+        assert_eq!(reader.metadata().num_row_groups(), 2);
+        let shipped = reader.metadata().file_metadata().schema_descr();
+        let columns: Vec<_> = shipped.columns().iter().map(|c| c.name().to_string()).collect();
+        assert_eq!(columns, ["day", "metric", "count", "updated_at"]);
+        assert!(shipped.columns().iter().all(|c| c.self_type().get_basic_info().repetition() == Repetition::REQUIRED));
+        assert_eq!(
+            metrics(file),
+            [("workflow_run".to_string(), 10), ("api_call".to_string(), 11), ("discovery_run".to_string(), 12)]
+        );
+        let published: TableSchema =
+            serde_json::from_slice(&object(bucket.as_ref(), "exports/daily_metrics/_schema.json").await).unwrap();
+        assert_eq!(published, Dataset::DailyMetrics.schema());
+
+        // Only the rows changed since the watermark ship next time: an updated counter and the
+        // row that has settled since
+        source.put(tenant, Dataset::DailyMetrics, metric(24, "workflow_run", 15, t(70)));
+        let second = service.run_now(tenant, export.id, t(120)).await.unwrap();
+        assert_eq!(second.watermark_from, *first.watermark_to.as_ref().unwrap());
+        assert_eq!(second.rows, 2);
+        assert_eq!(
+            metrics(object(bucket.as_ref(), &file_key(&second)).await),
+            [("api_call".to_string(), 1), ("workflow_run".to_string(), 15)]
+        );
+
+        let idle = service.run_now(tenant, export.id, t(180)).await.unwrap();
+        assert_eq!((idle.status, idle.rows, idle.file.as_ref()), (ExportRunStatus::Succeeded, 0, None));
+        assert_eq!(service.export(tenant, export.id).await.unwrap().progress.watermark, *second.watermark_to.as_ref().unwrap());
+
+        // A new nullable column ships with the next rows
+        let mut widened = Dataset::DailyMetrics.schema();
+        widened.columns.push(Column::nullable("source", ColumnType::String));
+        source.set_schema(Dataset::DailyMetrics, widened.clone());
+        let mut row = metric(25, "workflow_run", 3, t(200));
+        row.values.push(Value::from("scheduler"));
+        source.put(tenant, Dataset::DailyMetrics, row);
+        let third = service.run_now(tenant, export.id, t(240)).await.unwrap();
+        assert_eq!(third.status, ExportRunStatus::Succeeded);
+        assert_eq!(third.schema_changes, [SchemaChange::Added { column: Column::nullable("source", ColumnType::String) }]);
+        let file = object(bucket.as_ref(), &file_key(&third)).await;
+        let reader = SerializedFileReader::new(axum::body::Bytes::from(file)).unwrap();
+        let source_column = reader.metadata().file_metadata().schema_descr().column(4);
+        assert_eq!(source_column.name(), "source");
+        assert_eq!(source_column.self_type().get_basic_info().repetition(), Repetition::OPTIONAL);
+        let published: TableSchema =
+            serde_json::from_slice(&object(bucket.as_ref(), "exports/daily_metrics/_schema.json").await).unwrap();
+        assert_eq!(published, widened);
+
+        // Dropping a column would break the table, so the export stops without shipping
+        let mut narrowed = widened.clone();
+        narrowed.columns.retain(|c| c.name != "count");
+        source.set_schema(Dataset::DailyMetrics, narrowed);
+        source.put(
+            tenant,
+            Dataset::DailyMetrics,
+            SourceRow {
+                values: vec![NaiveDate::from_ymd_opt(2025, 7, 25).unwrap().into(), "api_call".into(), t(250).into(), Value::Null],
+                watermark: Watermark { updated_at: t(250), key: "2025-07-25/api_call".into() },
+            },
+        );
+        let blocked = service.run_now(tenant, export.id, t(300)).await.unwrap();
+        assert_eq!(blocked.status, ExportRunStatus::Blocked);
+        assert_eq!(blocked.schema_changes, [SchemaChange::Removed { name: "count".into() }]);
+        let stopped = service.export(tenant, export.id).await.unwrap();
+        assert_eq!(stopped.progress.watermark, *third.watermark_to.as_ref().unwrap());
+        assert_eq!(stopped.progress.blocked_changes, blocked.schema_changes);
+        assert_eq!(service.run_now(tenant, export.id, t(310)).await.unwrap().status, ExportRunStatus::Blocked);
+
+        service.accept_schema(tenant, export.id, false).await.unwrap();
+        let resumed = service.run_now(tenant, export.id, t(360)).await.unwrap();
+        assert_eq!((resumed.status, resumed.rows), (ExportRunStatus::Succeeded, 1));
+        assert!(resumed.schema_changes.is_empty());
+
+        let runs = service.runs(tenant, export.id, 10).await.unwrap();
+        assert_eq!(runs.len(), 7);
+        assert_eq!(runs[0].id, resumed.id);
+        assert!(runs.iter().all(|r| r.finished_at.is_some()));
+    }
+}
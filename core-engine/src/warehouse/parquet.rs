@@ -0,0 +1,306 @@
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use axum::body::Bytes;
+use chrono::{DateTime, NaiveDate};
+use parquet::basic::{Compression, LogicalType, Repetition, TimeUnit, Type as PhysicalType};
+use parquet::column::writer::ColumnWriterImpl;
+use parquet::data_type::{BoolType, ByteArray, ByteArrayType, DataType, DoubleType, Int32Type, Int64Type};
+use parquet::errors::ParquetError;
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::{SerializedColumnWriter, SerializedFileWriter};
+use parquet::format::MicroSeconds;
+use parquet::schema::types::Type;
+
+use super::schema::{Column, ColumnType, Row, TableSchema, Value};
+use crate::error::{AppError, AppResult};
+
+pub const CONTENT_TYPE: &str = "application/vnd.apache.parquet";
+
+fn parquet_error(e: ParquetError) -> AppError {
+    AppError::Serialization(format!("Failed to write parquet: {}", e))
+}
+
+// Sink the file writer writes into; drained after every row group so the file never has to
+// be held in memory whole
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl SharedBuffer {
+    fn take(&self) -> Bytes {
+        Bytes::from(std::mem::take(&mut *self.0.lock().unwrap()))
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+fn parquet_type(column: &Column) -> AppResult<Type> {
+    let (physical, logical) = match column.kind {
+        ColumnType::String => (PhysicalType::BYTE_ARRAY, Some(LogicalType::String)),
+        ColumnType::Json => (PhysicalType::BYTE_ARRAY, Some(LogicalType::Json)),
+        ColumnType::Int64 => (PhysicalType::INT64, None),
+        ColumnType::Float64 => (PhysicalType::DOUBLE, None),
+        ColumnType::Bool => (PhysicalType::BOOLEAN, None),
+        ColumnType::Timestamp => (
+            PhysicalType::INT64,
+            Some(LogicalType::Timestamp { is_adjusted_to_u_t_c: true, unit: TimeUnit::MICROS(MicroSeconds {}) }),
+        ),
+        ColumnType::Date => (PhysicalType::INT32, Some(LogicalType::Date)),
+    };
+    Type::primitive_type_builder(&column.name, physical)
+        .with_logical_type(logical)
+        .with_repetition(if column.nullable { Repetition::OPTIONAL } else { Repetition::REQUIRED })
+        .build()
+        .map_err(parquet_error)
+}
+
+pub fn message_type(schema: &TableSchema) -> AppResult<Type> {
+    let fields = schema.columns.iter().map(|c| parquet_type(c).map(Arc::new)).collect::<AppResult<Vec<_>>>()?;
+    Type::group_type_builder("schema").with_fields(fields).build().map_err(parquet_error)
+}
+
+fn epoch_days(date: NaiveDate) -> i32 {
+    (date - DateTime::UNIX_EPOCH.date_naive()).num_days() as i32
+}
+
+// The column's non-null values and, for nullable columns, its definition levels
+fn collect<T>(column: &Column, index: usize, rows: &[Row], convert: impl Fn(&Value) -> Option<T>) -> AppResult<(Vec<T>, Vec<i16>)> {
+    let mut values = Vec::with_capacity(rows.len());
+    let mut levels = Vec::with_capacity(rows.len());
+    for row in rows {
+        let value = row.get(index).unwrap_or(&Value::Null);
+        if value.is_null() {
+            if !column.nullable {
+                return Err(AppError::Serialization(format!("Column {} is required but a row has no value", column.name)));
+            }
+            levels.push(0);
+            continue;
+        }
+        let converted = convert(value).ok_or_else(|| {
+            AppError::Serialization(format!("Column {} expects {} values, got {:?}", column.name, column.kind.as_str(), value))
+        })?;
+        values.push(converted);
+        levels.push(1);
+    }
+    Ok((values, levels))
+}
+
+fn write_values<T: DataType>(writer: &mut ColumnWriterImpl<'_, T>, column: &Column, values: &[T::T], levels: &[i16]) -> AppResult<()> {
+    let levels = column.nullable.then_some(levels);
+    writer.write_batch(values, levels, None).map_err(parquet_error)?;
+    Ok(())
+}
+
+fn write_column(writer: &mut SerializedColumnWriter<'_>, column: &Column, index: usize, rows: &[Row]) -> AppResult<()> {
+    match column.kind {
+        ColumnType::String | ColumnType::Json => {
+            let (values, levels) = collect(column, index, rows, |v| match v {
+                Value::String(s) => Some(ByteArray::from(s.as_str())),
+                _ => None,
+            })?;
+            write_values(writer.typed::<ByteArrayType>(), column, &values, &levels)
+        }
+        ColumnType::Int64 => {
+            let (values, levels) = collect(column, index, rows, |v| match v {
+                Value::Int64(n) => Some(*n),
+                _ => None,
+            })?;
+            write_values(writer.typed::<Int64Type>(), column, &values, &levels)
+        }
+        ColumnType::Float64 => {
+            let (values, levels) = collect(column, index, rows, |v| match v {
+                Value::Float64(n) => Some(*n),
+                Value::Int64(n) => Some(*n as f64),
+                _ => None,
+            })?;
+            write_values(writer.typed::<DoubleType>(), column, &values, &levels)
+        }
+        ColumnType::Bool => {
+            let (values, levels) = collect(column, index, rows, |v| match v {
+                Value::Bool(b) => Some(*b),
+                _ => None,
+            })?;
+            write_values(writer.typed::<BoolType>(), column, &values, &levels)
+        }
+        ColumnType::Timestamp => {
+            let (values, levels) = collect(column, index, rows, |v| match v {
+                Value::Timestamp(at) => Some(at.timestamp_micros()),
+                _ => None,
+            })?;
+            write_values(writer.typed::<Int64Type>(), column, &values, &levels)
+        }
+        ColumnType::Date => {
+            let (values, levels) = collect(column, index, rows, |v| match v {
+                Value::Date(date) => Some(epoch_days(*date)),
+                _ => None,
+            })?;
+            write_values(writer.typed::<Int32Type>(), column, &values, &levels)
+        }
+    }
+}
+
+// Writes a parquet file one row group at a time. Each call hands back the bytes written so
+// far, so callers can stream them to storage while holding only the current batch.
+pub struct ParquetWriter {
+    writer: SerializedFileWriter<SharedBuffer>,
+    buffer: SharedBuffer,
+    columns: Vec<Column>,
+    rows: u64,
+}
+
+impl ParquetWriter {
+    pub fn new(schema: &TableSchema) -> AppResult<Self> {
+        let buffer = SharedBuffer::default();
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .set_created_by("sirsi-warehouse-export".into())
+            .build();
+        let writer = SerializedFileWriter::new(buffer.clone(), Arc::new(message_type(schema)?), Arc::new(properties))
+            .map_err(parquet_error)?;
+        Ok(Self { writer, buffer, columns: schema.columns.clone(), rows: 0 })
+    }
+
+    pub fn rows(&self) -> u64 {
+        self.rows
+    }
+
+    pub fn write_row_group(&mut self, rows: &[Row]) -> AppResult<Bytes> {
+        if rows.is_empty() {
+            return Ok(self.buffer.take());
+        }
+        let mut group = self.writer.next_row_group().map_err(parquet_error)?;
+        let mut index = 0;
+        while let Some(mut writer) = group.next_column().map_err(parquet_error)? {
+            let column = self
+                .columns
+                .get(index)
+                .ok_or_else(|| AppError::Internal("Parquet schema has more columns than the table".into()))?;
+            write_column(&mut writer, column, index, rows)?;
+            writer.close().map_err(parquet_error)?;
+            index += 1;
+        }
+        group.close().map_err(parquet_error)?;
+        self.rows += rows.len() as u64;
+        Ok(self.buffer.take())
+    }
+
+    // Writes the footer; the bytes returned end the file
+    pub fn finish(self) -> AppResult<Bytes> {
+        self.writer.close().map_err(parquet_error)?;
+        Ok(self.buffer.take())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use parquet::record::RowAccessor;
+
+    fn schema() -> TableSchema {
+        TableSchema::new(vec![
+            Column::required("id", ColumnType::String),
+            Column::nullable("name", ColumnType::String),
+            Column::required("tags", ColumnType::Json),
+            Column::nullable("count", ColumnType::Int64),
+            Column::required("ratio", ColumnType::Float64),
+            Column::required("active", ColumnType::Bool),
+            Column::required("updated_at", ColumnType::Timestamp),
+            Column::nullable("day", ColumnType::Date),
+        ])
+    }
+
+    #[test]
+    fn test_streamed_file_schema_and_values() {
+        let at = Utc.with_ymd_and_hms(2025, 7, 24, 9, 30, 0).unwrap() + chrono::Duration::microseconds(17);
+        let day = NaiveDate::from_ymd_opt(2025, 7, 24).unwrap();
+        let row = |i: i64| -> Row {
+            vec![
+                format!("r-{}", i).into(),
+                if i % 2 == 0 { Value::Null } else { format!("name-{}", i).into() },
+                r#"{"env":"prod"}"#.into(),
+                if i % 3 == 0 { Value::Null } else { i.into() },
+                Value::Float64(i as f64 / 4.0),
+                Value::Bool(i % 2 == 0),
+                at.into(),
+                Some(day).into(),
+            ]
+        };
+
+        let mut writer = ParquetWriter::new(&schema()).unwrap();
+        let mut file = Vec::new();
+        let mut chunks = 0;
+        for group in 0..3 {
+            let rows: Vec<Row> = (group * 100..(group + 1) * 100).map(row).collect();
+            let bytes = writer.write_row_group(&rows).unwrap();
+            // Every row group reaches the caller as soon as it is written
+            assert!(!bytes.is_empty());
+            file.extend_from_slice(&bytes);
+            chunks += 1;
+        }
+        assert_eq!(writer.rows(), 300);
+        file.extend_from_slice(&writer.finish().unwrap());
+        assert_eq!(chunks, 3);
+        assert!(file.starts_with(b"PAR1") && file.ends_with(b"PAR1"));
+
+        let reader = SerializedFileReader::new(Bytes::from(file)).unwrap();
+        let metadata = reader.metadata();
+        assert_eq!(metadata.num_row_groups(), 3);
+        assert_eq!(metadata.file_metadata().num_rows(), 300);
+        let columns = metadata.file_metadata().schema_descr().columns().to_vec();
+        let described: Vec<_> = columns
+            .iter()
+            .map(|c| (c.name().to_string(), c.physical_type(), c.self_type().get_basic_info().repetition()))
+            .collect();
+        assert_eq!(
+            described,
+            [
+                ("id".to_string(), PhysicalType::BYTE_ARRAY, Repetition::REQUIRED),
+                ("name".to_string(), PhysicalType::BYTE_ARRAY, Repetition::OPTIONAL),
+                ("tags".to_string(), PhysicalType::BYTE_ARRAY, Repetition::REQUIRED),
+                ("count".to_string(), PhysicalType::INT64, Repetition::OPTIONAL),
+                ("ratio".to_string(), PhysicalType::DOUBLE, Repetition::REQUIRED),
+                ("active".to_string(), PhysicalType::BOOLEAN, Repetition::REQUIRED),
+                ("updated_at".to_string(), PhysicalType::INT64, Repetition::REQUIRED),
+                ("day".to_string(), PhysicalType::INT32, Repetition::OPTIONAL),
+            ]
+        );
+        assert_eq!(columns[2].logical_type(), Some(LogicalType::Json));
+        assert!(matches!(
+            columns[6].logical_type(),
+            Some(LogicalType::Timestamp { is_adjusted_to_u_t_c: true, unit: TimeUnit::MICROS(_) })
+        ));
+        assert_eq!(columns[7].logical_type(), Some(LogicalType::Date));
+
+        let rows: Vec<_> = reader.get_row_iter(None).unwrap().map(|r| r.unwrap()).collect();
+        assert_eq!(rows.len(), 300);
+        let third = &rows[3];
+        assert_eq!(third.get_string(0).unwrap(), "r-3");
+        assert_eq!(third.get_string(1).unwrap(), "name-3");
+        assert!(third.get_long(3).is_err());
+        assert_eq!(third.get_double(4).unwrap(), 0.75);
+        assert_eq!(third.get_timestamp_micros(6).unwrap(), at.timestamp_micros());
+        assert_eq!(third.get_date(7).unwrap(), epoch_days(day));
+        assert_eq!(rows[4].get_long(3).unwrap(), 4);
+    }
+
+    #[test]
+    fn test_rejects_values_that_dont_fit_the_schema() {
+        let mut row: Row = vec![Value::Null; 8];
+        let written = ParquetWriter::new(&schema()).unwrap().write_row_group(&[row.clone()]);
+        assert!(matches!(written, Err(AppError::Serialization(e)) if e.contains("id is required")));
+        row[0] = Value::Int64(1);
+        let written = ParquetWriter::new(&schema()).unwrap().write_row_group(&[row]);
+        assert!(matches!(written, Err(AppError::Serialization(e)) if e.contains("expects string")));
+    }
+}
@@ -0,0 +1,172 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColumnType {
+    String,
+    // JSON text; warehouses get it as a string column
+    Json,
+    Int64,
+    Float64,
+    Bool,
+    // Microseconds, UTC
+    Timestamp,
+    Date,
+}
+
+impl ColumnType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ColumnType::String => "string",
+            ColumnType::Json => "json",
+            ColumnType::Int64 => "int64",
+            ColumnType::Float64 => "float64",
+            ColumnType::Bool => "bool",
+            ColumnType::Timestamp => "timestamp",
+            ColumnType::Date => "date",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Column {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: ColumnType,
+    pub nullable: bool,
+}
+
+impl Column {
+    pub fn required(name: impl Into<String>, kind: ColumnType) -> Self {
+        Self { name: name.into(), kind, nullable: false }
+    }
+
+    pub fn nullable(name: impl Into<String>, kind: ColumnType) -> Self {
+        Self { name: name.into(), kind, nullable: true }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TableSchema {
+    pub columns: Vec<Column>,
+}
+
+impl TableSchema {
+    pub fn new(columns: Vec<Column>) -> Self {
+        Self { columns }
+    }
+
+    pub fn column(&self, name: &str) -> Option<&Column> {
+        self.columns.iter().find(|c| c.name == name)
+    }
+
+    // What changed since `previous`, in column order; columns are matched by name, so
+    // reordering alone is no change
+    pub fn changes_since(&self, previous: &TableSchema) -> Vec<SchemaChange> {
+        let mut changes = Vec::new();
+        for column in &self.columns {
+            match previous.column(&column.name) {
+                None => changes.push(SchemaChange::Added { column: column.clone() }),
+                Some(before) if before.kind != column.kind => changes.push(SchemaChange::TypeChanged {
+                    name: column.name.clone(),
+                    from: before.kind,
+                    to: column.kind,
+                }),
+                Some(before) if before.nullable && !column.nullable => {
+                    changes.push(SchemaChange::Tightened { name: column.name.clone() })
+                }
+                Some(before) if !before.nullable && column.nullable => {
+                    changes.push(SchemaChange::Relaxed { name: column.name.clone() })
+                }
+                Some(_) => {}
+            }
+        }
+        for column in &previous.columns {
+            if self.column(&column.name).is_none() {
+                changes.push(SchemaChange::Removed { name: column.name.clone() });
+            }
+        }
+        changes
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum SchemaChange {
+    Added { column: Column },
+    Removed { name: String },
+    TypeChanged { name: String, from: ColumnType, to: ColumnType },
+    // A nullable column became required
+    Tightened { name: String },
+    // A required column became nullable
+    Relaxed { name: String },
+}
+
+impl SchemaChange {
+    // Breaking changes need the destination table changed by hand, so exports stop on them
+    // until the new schema is accepted. Existing rows can't fill a new required column either.
+    pub fn is_breaking(&self) -> bool {
+        match self {
+            SchemaChange::Added { column } => !column.nullable,
+            SchemaChange::Relaxed { .. } => false,
+            SchemaChange::Removed { .. } | SchemaChange::TypeChanged { .. } | SchemaChange::Tightened { .. } => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    String(String),
+    Int64(i64),
+    Float64(f64),
+    Bool(bool),
+    Timestamp(DateTime<Utc>),
+    Date(NaiveDate),
+}
+
+impl Value {
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::String(value)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::String(value.to_string())
+    }
+}
+
+impl From<i64> for Value {
+    fn from(value: i64) -> Self {
+        Value::Int64(value)
+    }
+}
+
+impl From<DateTime<Utc>> for Value {
+    fn from(value: DateTime<Utc>) -> Self {
+        Value::Timestamp(value)
+    }
+}
+
+impl From<NaiveDate> for Value {
+    fn from(value: NaiveDate) -> Self {
+        Value::Date(value)
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Value::Null, Into::into)
+    }
+}
+
+// One value per schema column, in column order
+pub type Row = Vec<Value>;
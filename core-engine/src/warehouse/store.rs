@@ -0,0 +1,517 @@
+use std::collections::HashMap;
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::types::Json;
+use sqlx::PgPool; // CockroachDB uses PostgreSQL protocol
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use super::{
+    Dataset, Destination, DestinationConfig, ExportDefinition, ExportProgress, ExportRun, ExportRunStatus, RunTrigger,
+    SchemaChange, TableSchema, WarehouseStore, Watermark,
+};
+use crate::error::{AppError, AppResult};
+
+#[derive(sqlx::FromRow)]
+struct DestinationRow {
+    id: Uuid,
+    tenant_id: Uuid,
+    name: String,
+    config: Json<DestinationConfig>,
+    created_at: DateTime<Utc>,
+}
+
+impl From<DestinationRow> for Destination {
+    fn from(row: DestinationRow) -> Self {
+        Self { id: row.id, tenant_id: row.tenant_id, name: row.name, config: row.config.0, created_at: row.created_at }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct ExportRow {
+    id: Uuid,
+    tenant_id: Uuid,
+    destination_id: Uuid,
+    dataset: Dataset,
+    table_name: String,
+    interval_minutes: i64,
+    created_at: DateTime<Utc>,
+    last_scheduled_for: Option<DateTime<Utc>>,
+    watermark: Json<Watermark>,
+    shipped_schema: Option<Json<TableSchema>>,
+    blocked_changes: Json<Vec<SchemaChange>>,
+}
+
+impl From<ExportRow> for ExportDefinition {
+    fn from(row: ExportRow) -> Self {
+        Self {
+            id: row.id,
+            tenant_id: row.tenant_id,
+            destination_id: row.destination_id,
+            dataset: row.dataset,
+            table: row.table_name,
+            interval_minutes: row.interval_minutes,
+            created_at: row.created_at,
+            last_scheduled_for: row.last_scheduled_for,
+            progress: ExportProgress {
+                watermark: row.watermark.0,
+                schema: row.shipped_schema.map(|s| s.0),
+                blocked_changes: row.blocked_changes.0,
+            },
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct RunRow {
+    id: Uuid,
+    export_id: Uuid,
+    tenant_id: Uuid,
+    trigger: RunTrigger,
+    status: ExportRunStatus,
+    rows: i64,
+    bytes: i64,
+    file: Option<String>,
+    watermark_from: Json<Watermark>,
+    watermark_to: Option<Json<Watermark>>,
+    schema_changes: Json<Vec<SchemaChange>>,
+    error: Option<String>,
+    started_at: DateTime<Utc>,
+    finished_at: Option<DateTime<Utc>>,
+}
+
+impl From<RunRow> for ExportRun {
+    fn from(row: RunRow) -> Self {
+        Self {
+            id: row.id,
+            export_id: row.export_id,
+            tenant_id: row.tenant_id,
+            trigger: row.trigger,
+            status: row.status,
+            rows: row.rows as u64,
+            bytes: row.bytes as u64,
+            file: row.file,
+            watermark_from: row.watermark_from.0,
+            watermark_to: row.watermark_to.map(|w| w.0),
+            schema_changes: row.schema_changes.0,
+            error: row.error,
+            started_at: row.started_at,
+            finished_at: row.finished_at,
+        }
+    }
+}
+
+const DESTINATION_COLUMNS: &str = "id, tenant_id, name, config, created_at";
+const EXPORT_COLUMNS: &str = "id, tenant_id, destination_id, dataset, table_name, interval_minutes, created_at, \
+    last_scheduled_for, watermark, shipped_schema, blocked_changes";
+const RUN_COLUMNS: &str = "id, export_id, tenant_id, trigger, status, rows, bytes, file, watermark_from, watermark_to, \
+    schema_changes, error, started_at, finished_at";
+
+pub struct PgWarehouseStore {
+    pool: PgPool,
+}
+
+impl PgWarehouseStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl WarehouseStore for PgWarehouseStore {
+    async fn create_destination(&self, destination: &Destination) -> AppResult<()> {
+        sqlx::query(
+            r#"INSERT INTO warehouse_destinations (id, tenant_id, name, config, created_at)
+            VALUES ($1, $2, $3, $4, $5)"#
+        )
+        .bind(destination.id)
+        .bind(destination.tenant_id)
+        .bind(&destination.name)
+        .bind(Json(&destination.config))
+        .bind(destination.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(())
+    }
+
+    async fn get_destination(&self, tenant_id: Uuid, id: Uuid) -> AppResult<Option<Destination>> {
+        let row = sqlx::query_as::<_, DestinationRow>(&format!(
+            "SELECT {} FROM warehouse_destinations WHERE id = $1 AND tenant_id = $2",
+            DESTINATION_COLUMNS
+        ))
+        .bind(id)
+        .bind(tenant_id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(row.map(Into::into))
+    }
+
+    async fn list_destinations(&self, tenant_id: Uuid) -> AppResult<Vec<Destination>> {
+        let rows = sqlx::query_as::<_, DestinationRow>(&format!(
+            "SELECT {} FROM warehouse_destinations WHERE tenant_id = $1 ORDER BY created_at, id",
+            DESTINATION_COLUMNS
+        ))
+        .bind(tenant_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+
+    async fn delete_destination(&self, tenant_id: Uuid, id: Uuid) -> AppResult<bool> {
+        let result = sqlx::query("DELETE FROM warehouse_destinations WHERE id = $1 AND tenant_id = $2")
+            .bind(id)
+            .bind(tenant_id)
+            .execute(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn create_export(&self, export: &ExportDefinition) -> AppResult<()> {
+        sqlx::query(
+            r#"INSERT INTO warehouse_exports
+            (id, tenant_id, destination_id, dataset, table_name, interval_minutes, created_at, watermark, shipped_schema, blocked_changes)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)"#
+        )
+        .bind(export.id)
+        .bind(export.tenant_id)
+        .bind(export.destination_id)
+        .bind(export.dataset)
+        .bind(&export.table)
+        .bind(export.interval_minutes)
+        .bind(export.created_at)
+        .bind(Json(&export.progress.watermark))
+        .bind(export.progress.schema.as_ref().map(Json))
+        .bind(Json(&export.progress.blocked_changes))
+        .execute(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(ref db) if db.is_unique_violation() => AppError::Conflict {
+                message: format!("The destination already has an export into {}", export.table),
+                current: None,
+            },
+            e => AppError::Database(e),
+        })?;
+
+        Ok(())
+    }
+
+    async fn get_export(&self, tenant_id: Uuid, id: Uuid) -> AppResult<Option<ExportDefinition>> {
+        let row = sqlx::query_as::<_, ExportRow>(&format!(
+            "SELECT {} FROM warehouse_exports WHERE id = $1 AND tenant_id = $2",
+            EXPORT_COLUMNS
+        ))
+        .bind(id)
+        .bind(tenant_id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(row.map(Into::into))
+    }
+
+    async fn list_exports(&self, tenant_id: Option<Uuid>) -> AppResult<Vec<ExportDefinition>> {
+        let rows = sqlx::query_as::<_, ExportRow>(&format!(
+            "SELECT {} FROM warehouse_exports WHERE ($1::UUID IS NULL OR tenant_id = $1) ORDER BY created_at, id",
+            EXPORT_COLUMNS
+        ))
+        .bind(tenant_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+
+    async fn delete_export(&self, tenant_id: Uuid, id: Uuid) -> AppResult<bool> {
+        let result = sqlx::query("DELETE FROM warehouse_exports WHERE id = $1 AND tenant_id = $2")
+            .bind(id)
+            .bind(tenant_id)
+            .execute(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn claim_slot(&self, id: Uuid, previous: Option<DateTime<Utc>>, slot: DateTime<Utc>) -> AppResult<bool> {
+        let result = sqlx::query(
+            r#"UPDATE warehouse_exports SET last_scheduled_for = $3
+            WHERE id = $1 AND last_scheduled_for IS NOT DISTINCT FROM $2"#
+        )
+        .bind(id)
+        .bind(previous)
+        .bind(slot)
+        .execute(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn acquire_lease(&self, id: Uuid, now: DateTime<Utc>, until: DateTime<Utc>) -> AppResult<bool> {
+        let result = sqlx::query(
+            r#"UPDATE warehouse_exports SET lease_until = $3
+            WHERE id = $1 AND (lease_until IS NULL OR lease_until <= $2)"#
+        )
+        .bind(id)
+        .bind(now)
+        .bind(until)
+        .execute(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn create_run(&self, run: &ExportRun) -> AppResult<()> {
+        sqlx::query(
+            r#"INSERT INTO warehouse_export_runs
+            (id, export_id, tenant_id, trigger, status, rows, bytes, file, watermark_from, watermark_to, schema_changes,
+                error, started_at, finished_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)"#
+        )
+        .bind(run.id)
+        .bind(run.export_id)
+        .bind(run.tenant_id)
+        .bind(run.trigger)
+        .bind(run.status)
+        .bind(run.rows as i64)
+        .bind(run.bytes as i64)
+        .bind(&run.file)
+        .bind(Json(&run.watermark_from))
+        .bind(run.watermark_to.as_ref().map(Json))
+        .bind(Json(&run.schema_changes))
+        .bind(&run.error)
+        .bind(run.started_at)
+        .bind(run.finished_at)
+        .execute(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(())
+    }
+
+    async fn finish_run(&self, run: &ExportRun, progress: &ExportProgress) -> AppResult<()> {
+        let mut tx = self.pool.begin().await.map_err(AppError::Database)?;
+        sqlx::query(
+            r#"UPDATE warehouse_export_runs SET status = $2, rows = $3, bytes = $4, file = $5, watermark_to = $6,
+                schema_changes = $7, error = $8, finished_at = $9
+            WHERE id = $1"#
+        )
+        .bind(run.id)
+        .bind(run.status)
+        .bind(run.rows as i64)
+        .bind(run.bytes as i64)
+        .bind(&run.file)
+        .bind(run.watermark_to.as_ref().map(Json))
+        .bind(Json(&run.schema_changes))
+        .bind(&run.error)
+        .bind(run.finished_at)
+        .execute(&mut *tx)
+        .await
+        .map_err(AppError::Database)?;
+
+        sqlx::query(
+            r#"UPDATE warehouse_exports SET watermark = $2, shipped_schema = $3, blocked_changes = $4, lease_until = NULL
+            WHERE id = $1"#
+        )
+        .bind(run.export_id)
+        .bind(Json(&progress.watermark))
+        .bind(progress.schema.as_ref().map(Json))
+        .bind(Json(&progress.blocked_changes))
+        .execute(&mut *tx)
+        .await
+        .map_err(AppError::Database)?;
+        tx.commit().await.map_err(AppError::Database)?;
+
+        Ok(())
+    }
+
+    async fn save_progress(&self, tenant_id: Uuid, id: Uuid, progress: &ExportProgress) -> AppResult<bool> {
+        let result = sqlx::query(
+            r#"UPDATE warehouse_exports SET watermark = $3, shipped_schema = $4, blocked_changes = $5
+            WHERE id = $1 AND tenant_id = $2"#
+        )
+        .bind(id)
+        .bind(tenant_id)
+        .bind(Json(&progress.watermark))
+        .bind(progress.schema.as_ref().map(Json))
+        .bind(Json(&progress.blocked_changes))
+        .execute(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn list_runs(&self, tenant_id: Uuid, export_id: Uuid, limit: i64) -> AppResult<Vec<ExportRun>> {
+        let rows = sqlx::query_as::<_, RunRow>(&format!(
+            "SELECT {} FROM warehouse_export_runs WHERE tenant_id = $1 AND export_id = $2 ORDER BY started_at DESC, id LIMIT $3",
+            RUN_COLUMNS
+        ))
+        .bind(tenant_id)
+        .bind(export_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+}
+
+#[derive(Default)]
+struct InMemoryState {
+    destinations: HashMap<Uuid, Destination>,
+    exports: HashMap<Uuid, ExportDefinition>,
+    leases: HashMap<Uuid, DateTime<Utc>>,
+    runs: Vec<ExportRun>,
+}
+
+#[derive(Default)]
+pub struct InMemoryWarehouseStore {
+    state: Mutex<InMemoryState>,
+}
+
+impl InMemoryWarehouseStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl WarehouseStore for InMemoryWarehouseStore {
+    async fn create_destination(&self, destination: &Destination) -> AppResult<()> {
+        self.state.lock().await.destinations.insert(destination.id, destination.clone());
+        Ok(())
+    }
+
+    async fn get_destination(&self, tenant_id: Uuid, id: Uuid) -> AppResult<Option<Destination>> {
+        Ok(self.state.lock().await.destinations.get(&id).filter(|d| d.tenant_id == tenant_id).cloned())
+    }
+
+    async fn list_destinations(&self, tenant_id: Uuid) -> AppResult<Vec<Destination>> {
+        let state = self.state.lock().await;
+        let mut destinations: Vec<_> = state.destinations.values().filter(|d| d.tenant_id == tenant_id).cloned().collect();
+        destinations.sort_by_key(|d| (d.created_at, d.id));
+        Ok(destinations)
+    }
+
+    async fn delete_destination(&self, tenant_id: Uuid, id: Uuid) -> AppResult<bool> {
+        let mut state = self.state.lock().await;
+        if state.destinations.get(&id).is_none_or(|d| d.tenant_id != tenant_id) {
+            return Ok(false);
+        }
+        state.destinations.remove(&id);
+        Ok(true)
+    }
+
+    async fn create_export(&self, export: &ExportDefinition) -> AppResult<()> {
+        let mut state = self.state.lock().await;
+        if state.exports.values().any(|e| e.destination_id == export.destination_id && e.table == export.table) {
+            return Err(AppError::Conflict {
+                message: format!("The destination already has an export into {}", export.table),
+                current: None,
+            });
+        }
+        state.exports.insert(export.id, export.clone());
+        Ok(())
+    }
+
+    async fn get_export(&self, tenant_id: Uuid, id: Uuid) -> AppResult<Option<ExportDefinition>> {
+        Ok(self.state.lock().await.exports.get(&id).filter(|e| e.tenant_id == tenant_id).cloned())
+    }
+
+    async fn list_exports(&self, tenant_id: Option<Uuid>) -> AppResult<Vec<ExportDefinition>> {
+        let state = self.state.lock().await;
+        let mut exports: Vec<_> = state
+            .exports
+            .values()
+            .filter(|e| tenant_id.is_none_or(|t| e.tenant_id == t))
+            .cloned()
+            .collect();
+        exports.sort_by_key(|e| (e.created_at, e.id));
+        Ok(exports)
+    }
+
+    async fn delete_export(&self, tenant_id: Uuid, id: Uuid) -> AppResult<bool> {
+        let mut state = self.state.lock().await;
+        if state.exports.get(&id).is_none_or(|e| e.tenant_id != tenant_id) {
+            return Ok(false);
+        }
+        state.exports.remove(&id);
+        state.leases.remove(&id);
+        state.runs.retain(|r| r.export_id != id);
+        Ok(true)
+    }
+
+    async fn claim_slot(&self, id: Uuid, previous: Option<DateTime<Utc>>, slot: DateTime<Utc>) -> AppResult<bool> {
+        let mut state = self.state.lock().await;
+        match state.exports.get_mut(&id) {
+            Some(export) if export.last_scheduled_for == previous => {
+                export.last_scheduled_for = Some(slot);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn acquire_lease(&self, id: Uuid, now: DateTime<Utc>, until: DateTime<Utc>) -> AppResult<bool> {
+        let mut state = self.state.lock().await;
+        if !state.exports.contains_key(&id) || state.leases.get(&id).is_some_and(|held| *held > now) {
+            return Ok(false);
+        }
+        state.leases.insert(id, until);
+        Ok(true)
+    }
+
+    async fn create_run(&self, run: &ExportRun) -> AppResult<()> {
+        self.state.lock().await.runs.push(run.clone());
+        Ok(())
+    }
+
+    async fn finish_run(&self, run: &ExportRun, progress: &ExportProgress) -> AppResult<()> {
+        let mut state = self.state.lock().await;
+        if let Some(stored) = state.runs.iter_mut().find(|r| r.id == run.id) {
+            *stored = run.clone();
+        }
+        if let Some(export) = state.exports.get_mut(&run.export_id) {
+            export.progress = progress.clone();
+        }
+        state.leases.remove(&run.export_id);
+        Ok(())
+    }
+
+    async fn save_progress(&self, tenant_id: Uuid, id: Uuid, progress: &ExportProgress) -> AppResult<bool> {
+        let mut state = self.state.lock().await;
+        match state.exports.get_mut(&id) {
+            Some(export) if export.tenant_id == tenant_id => {
+                export.progress = progress.clone();
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn list_runs(&self, tenant_id: Uuid, export_id: Uuid, limit: i64) -> AppResult<Vec<ExportRun>> {
+        let state = self.state.lock().await;
+        let mut runs: Vec<_> = state
+            .runs
+            .iter()
+            .filter(|r| r.tenant_id == tenant_id && r.export_id == export_id)
+            .cloned()
+            .collect();
+        runs.sort_by(|a, b| b.started_at.cmp(&a.started_at).then_with(|| a.id.cmp(&b.id)));
+        runs.truncate(limit.max(0) as usize);
+        Ok(runs)
+    }
+}
@@ -0,0 +1,739 @@
+use std::collections::{hash_map::Entry, HashMap};
+use std::sync::Arc;
+use std::time::Duration as StdDuration;
+
+use axum::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::error::{AppError, AppResult};
+
+pub mod store;
+
+pub use store::{enqueue, InMemoryWebhookStore, PgWebhookStore};
+
+pub const SIGNATURE_HEADER: &str = "X-Sirsi-Signature";
+pub const TEST_EVENT_TYPE: &str = "webhook.test";
+const DEFAULT_MAX_ATTEMPTS: i64 = 8;
+const DEFAULT_BASE_BACKOFF_SECS: i64 = 10;
+const MAX_BACKOFF_SECS: i64 = 3600;
+const DEFAULT_FAILURE_THRESHOLD: i64 = 10;
+const DEFAULT_PAUSE_MINUTES: i64 = 30;
+// A claimed delivery whose dispatcher dies becomes due again after this long
+const DEFAULT_LEASE_SECS: i64 = 60;
+const BATCH_SIZE: i64 = 100;
+// Each round sends at most the head of every entity's queue
+const MAX_ROUNDS: usize = 20;
+const MAX_REDELIVERY_DAYS: i64 = 30;
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct WebhookEndpoint {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub url: String,
+    pub description: Option<String>,
+    // Empty means every event type
+    pub event_types: Vec<String>,
+    #[serde(skip_serializing)]
+    pub secret: String,
+    pub active: bool,
+    pub consecutive_failures: i64,
+    // Set by the circuit breaker; delivery resumes once it has passed
+    pub paused_until: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl WebhookEndpoint {
+    pub fn subscribes_to(&self, event_type: &str) -> bool {
+        self.event_types.is_empty() || self.event_types.iter().any(|t| t == event_type)
+    }
+}
+
+// What a mutation records alongside its change
+#[derive(Debug, Clone)]
+pub struct NewEvent {
+    pub tenant_id: Uuid,
+    pub event_type: String,
+    pub entity_type: String,
+    pub entity_id: Uuid,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+impl NewEvent {
+    pub fn new(tenant_id: Uuid, event_type: &str, entity_type: &str, entity_id: Uuid) -> Self {
+        Self {
+            tenant_id,
+            event_type: event_type.to_string(),
+            entity_type: entity_type.to_string(),
+            entity_id,
+            before: None,
+            after: None,
+        }
+    }
+
+    pub fn with_before(mut self, before: Value) -> Self {
+        self.before = Some(before);
+        self
+    }
+
+    pub fn with_after(mut self, after: Value) -> Self {
+        self.after = Some(after);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct OutboxEvent {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub event_type: String,
+    pub entity_type: String,
+    pub entity_id: Uuid,
+    // 1, 2, 3... per entity, in commit order
+    pub entity_seq: i64,
+    #[sqlx(rename = "before_state")]
+    pub before: Option<Value>,
+    #[sqlx(rename = "after_state")]
+    pub after: Option<Value>,
+    pub created_at: DateTime<Utc>,
+    // When deliveries were created for it
+    pub dispatched_at: Option<DateTime<Utc>>,
+}
+
+impl OutboxEvent {
+    pub fn payload(&self) -> Value {
+        json!({
+            "id": self.id,
+            "type": self.event_type,
+            "created_at": self.created_at,
+            "tenant_id": self.tenant_id,
+            "entity": { "type": self.entity_type, "id": self.entity_id, "sequence": self.entity_seq },
+            "data": { "before": self.before, "after": self.after },
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "VARCHAR", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryStatus {
+    Pending,
+    Delivered,
+    // Gave up after the last attempt; only a redelivery sends it again
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct WebhookDelivery {
+    pub id: Uuid,
+    pub event_id: Uuid,
+    pub endpoint_id: Uuid,
+    pub tenant_id: Uuid,
+    pub event_type: String,
+    pub entity_id: Uuid,
+    pub entity_seq: i64,
+    pub status: DeliveryStatus,
+    pub attempts: i64,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_status_code: Option<i32>,
+    pub last_error: Option<String>,
+    pub delivered_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl WebhookDelivery {
+    fn new(event: &OutboxEvent, endpoint_id: Uuid, now: DateTime<Utc>) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            event_id: event.id,
+            endpoint_id,
+            tenant_id: event.tenant_id,
+            event_type: event.event_type.clone(),
+            entity_id: event.entity_id,
+            entity_seq: event.entity_seq,
+            status: DeliveryStatus::Pending,
+            attempts: 0,
+            next_attempt_at: now,
+            last_status_code: None,
+            last_error: None,
+            delivered_at: None,
+            created_at: now,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeliveryAttempt {
+    pub success: bool,
+    pub status_code: Option<u16>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DispatchReport {
+    pub fanned_out: usize,
+    pub delivered: usize,
+    pub failed: usize,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EndpointRequest {
+    pub url: String,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub event_types: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct EndpointUpdate {
+    pub url: Option<String>,
+    pub description: Option<String>,
+    pub event_types: Option<Vec<String>>,
+    pub active: Option<bool>,
+}
+
+#[async_trait]
+pub trait WebhookStore: Send + Sync {
+    async fn create_endpoint(&self, endpoint: &WebhookEndpoint) -> AppResult<()>;
+    async fn get_endpoint(&self, tenant_id: Uuid, id: Uuid) -> AppResult<Option<WebhookEndpoint>>;
+    async fn list_endpoints(&self, tenant_id: Uuid) -> AppResult<Vec<WebhookEndpoint>>;
+    async fn update_endpoint(&self, endpoint: &WebhookEndpoint) -> AppResult<()>;
+    async fn delete_endpoint(&self, tenant_id: Uuid, id: Uuid) -> AppResult<bool>;
+    async fn set_circuit(&self, id: Uuid, consecutive_failures: i64, paused_until: Option<DateTime<Utc>>) -> AppResult<()>;
+
+    // For events outside a database mutation; mutations call `store::enqueue` in their own
+    // transaction instead
+    async fn append(&self, event: &NewEvent) -> AppResult<OutboxEvent>;
+    async fn get_event(&self, id: Uuid) -> AppResult<Option<OutboxEvent>>;
+    async fn undispatched(&self, limit: i64) -> AppResult<Vec<OutboxEvent>>;
+    // Inserts the deliveries and marks the event dispatched atomically
+    async fn fan_out(&self, event_id: Uuid, deliveries: &[WebhookDelivery], now: DateTime<Utc>) -> AppResult<()>;
+    async fn events_between(&self, tenant_id: Uuid, since: DateTime<Utc>, until: DateTime<Utc>) -> AppResult<Vec<OutboxEvent>>;
+
+    // Pending deliveries that are due, whose endpoint is active and not paused, and that have
+    // no earlier pending delivery for the same endpoint and entity
+    async fn due_deliveries(&self, now: DateTime<Utc>, limit: i64) -> AppResult<Vec<WebhookDelivery>>;
+    // Pushes `next_attempt_at` out to the lease, if it is still `seen`
+    async fn claim_delivery(&self, id: Uuid, seen: DateTime<Utc>, lease_until: DateTime<Utc>) -> AppResult<bool>;
+    async fn save_delivery(&self, delivery: &WebhookDelivery) -> AppResult<()>;
+    async fn list_deliveries(&self, tenant_id: Uuid, endpoint_id: Uuid, limit: i64) -> AppResult<Vec<WebhookDelivery>>;
+    // Back to pending with no attempts, creating the delivery if the endpoint never had one
+    async fn requeue(&self, deliveries: &[WebhookDelivery]) -> AppResult<usize>;
+}
+
+// Posts a signed payload; `Ok` carries the response status whatever it was
+#[async_trait]
+pub trait WebhookSender: Send + Sync {
+    async fn send(&self, url: &str, headers: &[(String, String)], body: &[u8]) -> Result<u16, String>;
+}
+
+// Told when the circuit breaker pauses an endpoint
+#[async_trait]
+pub trait WebhookAlerter: Send + Sync {
+    async fn endpoint_paused(&self, endpoint: &WebhookEndpoint, last_error: &str);
+}
+
+pub struct HttpWebhookSender {
+    client: reqwest::Client,
+}
+
+impl HttpWebhookSender {
+    pub fn new(timeout: StdDuration) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .redirect(reqwest::redirect::Policy::none())
+            .user_agent("sirsi-webhooks")
+            .build()
+            .unwrap_or_default();
+        Self { client }
+    }
+}
+
+impl Default for HttpWebhookSender {
+    fn default() -> Self {
+        Self::new(StdDuration::from_secs(10))
+    }
+}
+
+#[async_trait]
+impl WebhookSender for HttpWebhookSender {
+    async fn send(&self, url: &str, headers: &[(String, String)], body: &[u8]) -> Result<u16, String> {
+        let mut request = self.client.post(url).header("Content-Type", "application/json").body(body.to_vec());
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+        request.send().await.map(|response| response.status().as_u16()).map_err(|e| e.to_string())
+    }
+}
+
+// `t=<unix seconds>,v1=<hex HMAC-SHA256 of "<t>.<body>">`, as receivers verify it
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    format!("t={},v1={:x}", timestamp, mac.finalize().into_bytes())
+}
+
+fn validate_url(url: &str) -> AppResult<()> {
+    match reqwest::Url::parse(url) {
+        Ok(parsed) if matches!(parsed.scheme(), "https" | "http") && parsed.host_str().is_some() => Ok(()),
+        _ => Err(AppError::Validation(format!("'{}' is not an http(s) URL", url))),
+    }
+}
+
+fn backoff(attempts: i64, base: Duration) -> Duration {
+    let doublings = (attempts - 1).clamp(0, 16) as u32;
+    (base * 2i32.pow(doublings)).min(Duration::seconds(MAX_BACKOFF_SECS))
+}
+
+pub struct WebhookService {
+    store: Arc<dyn WebhookStore>,
+    sender: Arc<dyn WebhookSender>,
+    alerter: Option<Arc<dyn WebhookAlerter>>,
+    max_attempts: i64,
+    base_backoff: Duration,
+    failure_threshold: i64,
+    pause: Duration,
+    lease: Duration,
+}
+
+impl WebhookService {
+    pub fn new(store: Arc<dyn WebhookStore>) -> Self {
+        Self {
+            store,
+            sender: Arc::new(HttpWebhookSender::default()),
+            alerter: None,
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            base_backoff: Duration::seconds(DEFAULT_BASE_BACKOFF_SECS),
+            failure_threshold: DEFAULT_FAILURE_THRESHOLD,
+            pause: Duration::minutes(DEFAULT_PAUSE_MINUTES),
+            lease: Duration::seconds(DEFAULT_LEASE_SECS),
+        }
+    }
+
+    pub fn with_sender(mut self, sender: Arc<dyn WebhookSender>) -> Self {
+        self.sender = sender;
+        self
+    }
+
+    pub fn with_alerter(mut self, alerter: Arc<dyn WebhookAlerter>) -> Self {
+        self.alerter = Some(alerter);
+        self
+    }
+
+    // Backoff doubles from `base_backoff` after each failed attempt, up to an hour
+    pub fn with_retry_policy(mut self, max_attempts: i64, base_backoff: Duration) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self.base_backoff = base_backoff;
+        self
+    }
+
+    // Consecutive failures (across all of an endpoint's deliveries) that pause it, and for how long
+    pub fn with_circuit_breaker(mut self, failure_threshold: i64, pause: Duration) -> Self {
+        self.failure_threshold = failure_threshold.max(1);
+        self.pause = pause;
+        self
+    }
+
+    pub fn with_lease(mut self, lease: Duration) -> Self {
+        self.lease = lease;
+        self
+    }
+
+    pub fn store(&self) -> &Arc<dyn WebhookStore> {
+        &self.store
+    }
+
+    // The secret is returned here and never again
+    pub async fn create_endpoint(&self, tenant_id: Uuid, request: EndpointRequest, now: DateTime<Utc>) -> AppResult<(WebhookEndpoint, String)> {
+        validate_url(&request.url)?;
+        let secret = format!("whsec_{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+        let endpoint = WebhookEndpoint {
+            id: Uuid::new_v4(),
+            tenant_id,
+            url: request.url,
+            description: request.description,
+            event_types: request.event_types,
+            secret: secret.clone(),
+            active: true,
+            consecutive_failures: 0,
+            paused_until: None,
+            created_at: now,
+            updated_at: now,
+        };
+        self.store.create_endpoint(&endpoint).await?;
+        info!("Registered webhook endpoint {} for tenant {}", endpoint.id, tenant_id);
+        Ok((endpoint, secret))
+    }
+
+    pub async fn endpoints(&self, tenant_id: Uuid) -> AppResult<Vec<WebhookEndpoint>> {
+        self.store.list_endpoints(tenant_id).await
+    }
+
+    pub async fn endpoint(&self, tenant_id: Uuid, id: Uuid) -> AppResult<WebhookEndpoint> {
+        self.store
+            .get_endpoint(tenant_id, id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Webhook endpoint not found".into()))
+    }
+
+    pub async fn update_endpoint(&self, tenant_id: Uuid, id: Uuid, update: EndpointUpdate, now: DateTime<Utc>) -> AppResult<WebhookEndpoint> {
+        let mut endpoint = self.endpoint(tenant_id, id).await?;
+        if let Some(url) = update.url {
+            validate_url(&url)?;
+            endpoint.url = url;
+        }
+        if update.description.is_some() {
+            endpoint.description = update.description;
+        }
+        if let Some(event_types) = update.event_types {
+            endpoint.event_types = event_types;
+        }
+        if let Some(active) = update.active {
+            endpoint.active = active;
+        }
+        endpoint.updated_at = now;
+        self.store.update_endpoint(&endpoint).await?;
+        Ok(endpoint)
+    }
+
+    pub async fn delete_endpoint(&self, tenant_id: Uuid, id: Uuid) -> AppResult<()> {
+        if !self.store.delete_endpoint(tenant_id, id).await? {
+            return Err(AppError::NotFound("Webhook endpoint not found".into()));
+        }
+        Ok(())
+    }
+
+    // Closes the circuit by hand once the receiver is fixed
+    pub async fn resume(&self, tenant_id: Uuid, id: Uuid) -> AppResult<WebhookEndpoint> {
+        let mut endpoint = self.endpoint(tenant_id, id).await?;
+        self.store.set_circuit(id, 0, None).await?;
+        endpoint.consecutive_failures = 0;
+        endpoint.paused_until = None;
+        Ok(endpoint)
+    }
+
+    pub async fn deliveries(&self, tenant_id: Uuid, endpoint_id: Uuid, limit: i64) -> AppResult<Vec<WebhookDelivery>> {
+        self.endpoint(tenant_id, endpoint_id).await?;
+        self.store.list_deliveries(tenant_id, endpoint_id, limit).await
+    }
+
+    // Sent straight away and outside the outbox, so it neither retries nor trips the breaker
+    pub async fn send_test(&self, tenant_id: Uuid, id: Uuid, now: DateTime<Utc>) -> AppResult<DeliveryAttempt> {
+        let endpoint = self.endpoint(tenant_id, id).await?;
+        let event = OutboxEvent {
+            id: Uuid::new_v4(),
+            tenant_id,
+            event_type: TEST_EVENT_TYPE.to_string(),
+            entity_type: "webhook_endpoint".to_string(),
+            entity_id: endpoint.id,
+            entity_seq: 0,
+            before: None,
+            after: Some(json!({ "url": endpoint.url })),
+            created_at: now,
+            dispatched_at: Some(now),
+        };
+        Ok(self.send(&endpoint, &event, 0, now).await)
+    }
+
+    // Queues every event from the window again, in order, whatever happened to it before
+    pub async fn redeliver(
+        &self,
+        tenant_id: Uuid,
+        endpoint_id: Uuid,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> AppResult<usize> {
+        if since >= until {
+            return Err(AppError::Validation("since must be before until".into()));
+        }
+        if until - since > Duration::days(MAX_REDELIVERY_DAYS) {
+            return Err(AppError::Validation(format!("Redelivery covers at most {} days", MAX_REDELIVERY_DAYS)));
+        }
+        let endpoint = self.endpoint(tenant_id, endpoint_id).await?;
+        let deliveries: Vec<WebhookDelivery> = self
+            .store
+            .events_between(tenant_id, since, until)
+            .await?
+            .iter()
+            .filter(|event| endpoint.subscribes_to(&event.event_type))
+            .map(|event| WebhookDelivery::new(event, endpoint.id, now))
+            .collect();
+        let queued = self.store.requeue(&deliveries).await?;
+        info!("Queued {} events for redelivery to webhook endpoint {}", queued, endpoint.id);
+        Ok(queued)
+    }
+
+    async fn fan_out(&self, now: DateTime<Utc>) -> AppResult<usize> {
+        let events = self.store.undispatched(BATCH_SIZE).await?;
+        let mut endpoints: HashMap<Uuid, Vec<WebhookEndpoint>> = HashMap::new();
+        for event in &events {
+            let tenant_endpoints = match endpoints.entry(event.tenant_id) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => entry.insert(self.store.list_endpoints(event.tenant_id).await?),
+            };
+            let deliveries: Vec<WebhookDelivery> = tenant_endpoints
+                .iter()
+                .filter(|endpoint| endpoint.active && endpoint.subscribes_to(&event.event_type))
+                .map(|endpoint| WebhookDelivery::new(event, endpoint.id, now))
+                .collect();
+            self.store.fan_out(event.id, &deliveries, now).await?;
+        }
+        Ok(events.len())
+    }
+
+    async fn send(&self, endpoint: &WebhookEndpoint, event: &OutboxEvent, attempt: i64, now: DateTime<Utc>) -> DeliveryAttempt {
+        let body = serde_json::to_vec(&event.payload()).unwrap_or_default();
+        let headers = vec![
+            ("X-Sirsi-Event-Id".to_string(), event.id.to_string()),
+            ("X-Sirsi-Event-Type".to_string(), event.event_type.clone()),
+            ("X-Sirsi-Delivery-Attempt".to_string(), attempt.to_string()),
+            (SIGNATURE_HEADER.to_string(), sign(&endpoint.secret, now.timestamp(), &body)),
+        ];
+        match self.sender.send(&endpoint.url, &headers, &body).await {
+            Ok(status) if (200..300).contains(&status) => DeliveryAttempt { success: true, status_code: Some(status), error: None },
+            Ok(status) => DeliveryAttempt { success: false, status_code: Some(status), error: Some(format!("HTTP {}", status)) },
+            Err(e) => DeliveryAttempt { success: false, status_code: None, error: Some(e) },
+        }
+    }
+
+    // Updates the endpoint's failure count, pausing it (and alerting) when it crosses the threshold
+    async fn record_outcome(&self, endpoint: &mut WebhookEndpoint, attempt: &DeliveryAttempt, now: DateTime<Utc>) -> AppResult<()> {
+        if attempt.success {
+            if endpoint.consecutive_failures > 0 || endpoint.paused_until.is_some() {
+                endpoint.consecutive_failures = 0;
+                endpoint.paused_until = None;
+                self.store.set_circuit(endpoint.id, 0, None).await?;
+            }
+            return Ok(());
+        }
+        endpoint.consecutive_failures += 1;
+        let was_paused = endpoint.paused_until.is_some();
+        if endpoint.consecutive_failures >= self.failure_threshold {
+            endpoint.paused_until = Some(now + self.pause);
+        }
+        self.store.set_circuit(endpoint.id, endpoint.consecutive_failures, endpoint.paused_until).await?;
+        if endpoint.paused_until.is_some() && !was_paused {
+            let last_error = attempt.error.as_deref().unwrap_or("unknown error");
+            error!(
+                "Paused webhook endpoint {} after {} consecutive failures: {}",
+                endpoint.id, endpoint.consecutive_failures, last_error
+            );
+            if let Some(alerter) = &self.alerter {
+                alerter.endpoint_paused(endpoint, last_error).await;
+            }
+        }
+        Ok(())
+    }
+
+    async fn deliver(&self, mut delivery: WebhookDelivery, endpoint: &mut WebhookEndpoint, now: DateTime<Utc>) -> AppResult<bool> {
+        let Some(event) = self.store.get_event(delivery.event_id).await? else {
+            warn!("Webhook delivery {} points at a missing event", delivery.id);
+            return Ok(false);
+        };
+        delivery.attempts += 1;
+        let attempt = self.send(endpoint, &event, delivery.attempts, now).await;
+        delivery.last_status_code = attempt.status_code.map(i32::from);
+        delivery.last_error = attempt.error.clone();
+        if attempt.success {
+            delivery.status = DeliveryStatus::Delivered;
+            delivery.delivered_at = Some(now);
+        } else if delivery.attempts >= self.max_attempts {
+            delivery.status = DeliveryStatus::Failed;
+            warn!("Giving up on webhook delivery {} after {} attempts", delivery.id, delivery.attempts);
+        } else {
+            delivery.next_attempt_at = now + backoff(delivery.attempts, self.base_backoff);
+        }
+        self.store.save_delivery(&delivery).await?;
+        self.record_outcome(endpoint, &attempt, now).await?;
+        Ok(attempt.success)
+    }
+
+    // Fans out new events, then works through due deliveries one entity position at a time
+    pub async fn dispatch_once(&self, now: DateTime<Utc>) -> AppResult<DispatchReport> {
+        let mut report = DispatchReport { fanned_out: self.fan_out(now).await?, ..Default::default() };
+        for _ in 0..MAX_ROUNDS {
+            let due = self.store.due_deliveries(now, BATCH_SIZE).await?;
+            if due.is_empty() {
+                break;
+            }
+            let mut by_endpoint: HashMap<Uuid, Vec<WebhookDelivery>> = HashMap::new();
+            for delivery in due {
+                by_endpoint.entry(delivery.endpoint_id).or_default().push(delivery);
+            }
+
+            // Endpoints deliver concurrently; each one's heads go in turn so the breaker sees
+            // failures as they happen
+            let rounds = by_endpoint.into_iter().map(|(endpoint_id, deliveries)| async move {
+                let mut outcome = (0usize, 0usize);
+                let Some(first) = deliveries.first() else {
+                    return Ok(outcome);
+                };
+                let Some(mut endpoint) = self.store.get_endpoint(first.tenant_id, endpoint_id).await? else {
+                    return Ok(outcome);
+                };
+                // Coming out of a pause, a single delivery decides whether it stays closed
+                let half_open = endpoint.consecutive_failures >= self.failure_threshold;
+                let take = if half_open { 1 } else { deliveries.len() };
+                for delivery in deliveries.into_iter().take(take) {
+                    if endpoint.paused_until.is_some_and(|until| until > now) {
+                        break;
+                    }
+                    if !self.store.claim_delivery(delivery.id, delivery.next_attempt_at, now + self.lease).await? {
+                        continue;
+                    }
+                    if self.deliver(delivery, &mut endpoint, now).await? {
+                        outcome.0 += 1;
+                    } else {
+                        outcome.1 += 1;
+                    }
+                }
+                AppResult::Ok(outcome)
+            });
+            let mut progressed = false;
+            for result in futures::future::join_all(rounds).await {
+                let (delivered, failed) = result?;
+                progressed |= delivered > 0;
+                report.delivered += delivered;
+                report.failed += failed;
+            }
+            if !progressed {
+                break;
+            }
+        }
+        Ok(report)
+    }
+
+    pub fn spawn_dispatcher(self: Arc<Self>, interval: StdDuration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                match self.dispatch_once(Utc::now()).await {
+                    Ok(report) if report.delivered + report.failed > 0 => {
+                        info!("Webhook deliveries: {} delivered, {} failed", report.delivered, report.failed)
+                    }
+                    Ok(_) => {}
+                    Err(e) => warn!("Webhook dispatch failed: {}", e),
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::sync::{Mutex, Notify};
+
+    #[derive(Default)]
+    struct RecordingSender {
+        received: Mutex<Vec<(Uuid, i64)>>,
+        // Fails the first attempt at each of these entity sequences
+        fail_once: Mutex<Vec<i64>>,
+        // When set, every send parks here until the test is done with it
+        hang: Option<Arc<Notify>>,
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl WebhookSender for RecordingSender {
+        async fn send(&self, _url: &str, headers: &[(String, String)], body: &[u8]) -> Result<u16, String> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            if let Some(hang) = &self.hang {
+                hang.notified().await;
+            }
+            let payload: Value = serde_json::from_slice(body).unwrap();
+            let signature = headers.iter().find(|(name, _)| name == SIGNATURE_HEADER).map(|(_, v)| v.clone()).unwrap();
+            assert!(signature.starts_with("t=") && signature.contains(",v1="));
+            let entity = payload["entity"]["id"].as_str().unwrap().parse().unwrap();
+            let seq = payload["entity"]["sequence"].as_i64().unwrap();
+            let mut fail_once = self.fail_once.lock().await;
+            if let Some(index) = fail_once.iter().position(|s| *s == seq) {
+                fail_once.remove(index);
+                return Ok(503);
+            }
+            self.received.lock().await.push((entity, seq));
+            Ok(204)
+        }
+    }
+
+    async fn setup(store: Arc<InMemoryWebhookStore>, sender: Arc<RecordingSender>) -> (WebhookService, Uuid) {
+        let service = WebhookService::new(store).with_sender(sender).with_retry_policy(5, Duration::seconds(1));
+        let tenant = Uuid::new_v4();
+        let request = EndpointRequest { url: "https://hooks.example.com/sirsi".into(), description: None, event_types: vec![] };
+        service.create_endpoint(tenant, request, Utc::now()).await.unwrap();
+        (service, tenant)
+    }
+
+    #[tokio::test]
+    async fn test_event_committed_before_a_crash_is_delivered_after_restart() {
+        let store = Arc::new(InMemoryWebhookStore::new());
+        let hang = Arc::new(Notify::new());
+        let stuck = Arc::new(RecordingSender { hang: Some(hang.clone()), ..Default::default() });
+        let (service, tenant) = setup(store.clone(), stuck.clone()).await;
+        let entity = Uuid::new_v4();
+        store.append(&NewEvent::new(tenant, "resource.created", "resource", entity)).await.unwrap();
+
+        // The dispatcher claims the delivery and dies mid-send
+        let dispatcher = Arc::new(service).spawn_dispatcher(StdDuration::from_millis(10));
+        while stuck.calls.load(Ordering::SeqCst) == 0 {
+            tokio::task::yield_now().await;
+        }
+        dispatcher.abort();
+        let _ = dispatcher.await;
+        assert!(stuck.received.lock().await.is_empty());
+
+        let sender = Arc::new(RecordingSender::default());
+        let restarted = WebhookService::new(store.clone()).with_sender(sender.clone());
+        // Still leased to the dead dispatcher until the lease runs out
+        assert_eq!(restarted.dispatch_once(Utc::now()).await.unwrap().delivered, 0);
+        let report = restarted.dispatch_once(Utc::now() + Duration::seconds(DEFAULT_LEASE_SECS + 1)).await.unwrap();
+        assert_eq!(report.delivered, 1);
+        assert_eq!(*sender.received.lock().await, vec![(entity, 1)]);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_updates_to_one_entity_deliver_in_order_across_retries() {
+        let store = Arc::new(InMemoryWebhookStore::new());
+        let sender = Arc::new(RecordingSender { fail_once: Mutex::new(vec![2, 5]), ..Default::default() });
+        let (service, tenant) = setup(store.clone(), sender.clone()).await;
+        let entity = Uuid::new_v4();
+        let writers: Vec<_> = (0..10)
+            .map(|_| {
+                let store = store.clone();
+                tokio::spawn(async move { store.append(&NewEvent::new(tenant, "resource.updated", "resource", entity)).await.unwrap() })
+            })
+            .collect();
+        for writer in writers {
+            writer.await.unwrap();
+        }
+        let other = Uuid::new_v4();
+        store.append(&NewEvent::new(tenant, "resource.created", "resource", other)).await.unwrap();
+
+        // Event 2 fails and holds back 3 onwards until its retry; the other entity isn't held up
+        let start = Utc::now();
+        let first = service.dispatch_once(start).await.unwrap();
+        assert_eq!((first.fanned_out, first.delivered, first.failed), (11, 2, 1));
+        let mut at = start;
+        for _ in 0..10 {
+            at += Duration::seconds(5);
+            service.dispatch_once(at).await.unwrap();
+        }
+        let received = sender.received.lock().await.clone();
+        let sequence: Vec<i64> = received.iter().filter(|(id, _)| *id == entity).map(|(_, seq)| *seq).collect();
+        assert_eq!(sequence, (1..=10).collect::<Vec<_>>());
+        assert!(received.contains(&(other, 1)));
+    }
+}
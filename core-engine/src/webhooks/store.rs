@@ -0,0 +1,526 @@
+use std::collections::HashMap;
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{PgConnection, PgPool}; // CockroachDB uses PostgreSQL protocol
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use super::{DeliveryStatus, NewEvent, OutboxEvent, WebhookDelivery, WebhookEndpoint, WebhookStore};
+use crate::error::{AppError, AppResult};
+
+const ENDPOINT_COLUMNS: &str =
+    "id, tenant_id, url, description, event_types, secret, active, consecutive_failures, paused_until, created_at, updated_at";
+const EVENT_COLUMNS: &str =
+    "id, tenant_id, event_type, entity_type, entity_id, entity_seq, before_state, after_state, created_at, dispatched_at";
+const DELIVERY_COLUMNS: &str = "id, event_id, endpoint_id, tenant_id, event_type, entity_id, entity_seq, status, attempts, \
+    next_attempt_at, last_status_code, last_error, delivered_at, created_at";
+
+// Writes the event on the caller's connection, so it commits or rolls back with the change
+// that produced it
+pub async fn enqueue(conn: &mut PgConnection, event: &NewEvent) -> AppResult<OutboxEvent> {
+    let sql = format!(
+        r#"INSERT INTO outbox_events (id, tenant_id, event_type, entity_type, entity_id, entity_seq, before_state, after_state)
+        SELECT $1, $2, $3, $4, $5, COALESCE(MAX(entity_seq), 0) + 1, $6, $7 FROM outbox_events WHERE entity_id = $5
+        RETURNING {}"#,
+        EVENT_COLUMNS
+    );
+    let event = sqlx::query_as::<_, OutboxEvent>(&sql)
+        .bind(Uuid::new_v4())
+        .bind(event.tenant_id)
+        .bind(&event.event_type)
+        .bind(&event.entity_type)
+        .bind(event.entity_id)
+        .bind(&event.before)
+        .bind(&event.after)
+        .fetch_one(conn)
+        .await
+        .map_err(AppError::Database)?;
+
+    Ok(event)
+}
+
+async fn upsert_delivery(conn: &mut PgConnection, delivery: &WebhookDelivery, reset: bool) -> AppResult<bool> {
+    let on_conflict = if reset {
+        "DO UPDATE SET status = 'pending', attempts = 0, next_attempt_at = excluded.next_attempt_at, \
+         last_status_code = NULL, last_error = NULL, delivered_at = NULL"
+    } else {
+        "DO NOTHING"
+    };
+    let sql = format!(
+        r#"INSERT INTO webhook_deliveries ({})
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
+        ON CONFLICT (event_id, endpoint_id) {}"#,
+        DELIVERY_COLUMNS, on_conflict
+    );
+    let result = sqlx::query(&sql)
+        .bind(delivery.id)
+        .bind(delivery.event_id)
+        .bind(delivery.endpoint_id)
+        .bind(delivery.tenant_id)
+        .bind(&delivery.event_type)
+        .bind(delivery.entity_id)
+        .bind(delivery.entity_seq)
+        .bind(delivery.status)
+        .bind(delivery.attempts)
+        .bind(delivery.next_attempt_at)
+        .bind(delivery.last_status_code)
+        .bind(&delivery.last_error)
+        .bind(delivery.delivered_at)
+        .bind(delivery.created_at)
+        .execute(conn)
+        .await
+        .map_err(AppError::Database)?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub struct PgWebhookStore {
+    pool: PgPool,
+}
+
+impl PgWebhookStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl WebhookStore for PgWebhookStore {
+    async fn create_endpoint(&self, endpoint: &WebhookEndpoint) -> AppResult<()> {
+        sqlx::query(&format!(
+            "INSERT INTO webhook_endpoints ({}) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)",
+            ENDPOINT_COLUMNS
+        ))
+        .bind(endpoint.id)
+        .bind(endpoint.tenant_id)
+        .bind(&endpoint.url)
+        .bind(&endpoint.description)
+        .bind(&endpoint.event_types)
+        .bind(&endpoint.secret)
+        .bind(endpoint.active)
+        .bind(endpoint.consecutive_failures)
+        .bind(endpoint.paused_until)
+        .bind(endpoint.created_at)
+        .bind(endpoint.updated_at)
+        .execute(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(())
+    }
+
+    async fn get_endpoint(&self, tenant_id: Uuid, id: Uuid) -> AppResult<Option<WebhookEndpoint>> {
+        let sql = format!("SELECT {} FROM webhook_endpoints WHERE tenant_id = $1 AND id = $2", ENDPOINT_COLUMNS);
+        let endpoint = sqlx::query_as::<_, WebhookEndpoint>(&sql)
+            .bind(tenant_id)
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(endpoint)
+    }
+
+    async fn list_endpoints(&self, tenant_id: Uuid) -> AppResult<Vec<WebhookEndpoint>> {
+        let sql = format!("SELECT {} FROM webhook_endpoints WHERE tenant_id = $1 ORDER BY created_at", ENDPOINT_COLUMNS);
+        let endpoints = sqlx::query_as::<_, WebhookEndpoint>(&sql)
+            .bind(tenant_id)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(endpoints)
+    }
+
+    async fn update_endpoint(&self, endpoint: &WebhookEndpoint) -> AppResult<()> {
+        sqlx::query(
+            r#"UPDATE webhook_endpoints SET url = $1, description = $2, event_types = $3, active = $4, updated_at = $5
+            WHERE tenant_id = $6 AND id = $7"#
+        )
+        .bind(&endpoint.url)
+        .bind(&endpoint.description)
+        .bind(&endpoint.event_types)
+        .bind(endpoint.active)
+        .bind(endpoint.updated_at)
+        .bind(endpoint.tenant_id)
+        .bind(endpoint.id)
+        .execute(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(())
+    }
+
+    async fn delete_endpoint(&self, tenant_id: Uuid, id: Uuid) -> AppResult<bool> {
+        let result = sqlx::query("DELETE FROM webhook_endpoints WHERE tenant_id = $1 AND id = $2")
+            .bind(tenant_id)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn set_circuit(&self, id: Uuid, consecutive_failures: i64, paused_until: Option<DateTime<Utc>>) -> AppResult<()> {
+        sqlx::query("UPDATE webhook_endpoints SET consecutive_failures = $1, paused_until = $2 WHERE id = $3")
+            .bind(consecutive_failures)
+            .bind(paused_until)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(())
+    }
+
+    async fn append(&self, event: &NewEvent) -> AppResult<OutboxEvent> {
+        let mut conn = self.pool.acquire().await.map_err(AppError::Database)?;
+        enqueue(&mut conn, event).await
+    }
+
+    async fn get_event(&self, id: Uuid) -> AppResult<Option<OutboxEvent>> {
+        let sql = format!("SELECT {} FROM outbox_events WHERE id = $1", EVENT_COLUMNS);
+        let event = sqlx::query_as::<_, OutboxEvent>(&sql)
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(event)
+    }
+
+    async fn undispatched(&self, limit: i64) -> AppResult<Vec<OutboxEvent>> {
+        let sql = format!(
+            "SELECT {} FROM outbox_events WHERE dispatched_at IS NULL ORDER BY created_at, entity_seq LIMIT $1",
+            EVENT_COLUMNS
+        );
+        let events = sqlx::query_as::<_, OutboxEvent>(&sql)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(events)
+    }
+
+    async fn fan_out(&self, event_id: Uuid, deliveries: &[WebhookDelivery], now: DateTime<Utc>) -> AppResult<()> {
+        let mut tx = self.pool.begin().await.map_err(AppError::Database)?;
+        // Another dispatcher may have fanned the same event out; its deliveries stand
+        for delivery in deliveries {
+            upsert_delivery(&mut tx, delivery, false).await?;
+        }
+        sqlx::query("UPDATE outbox_events SET dispatched_at = $1 WHERE id = $2 AND dispatched_at IS NULL")
+            .bind(now)
+            .bind(event_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(AppError::Database)?;
+        tx.commit().await.map_err(AppError::Database)?;
+
+        Ok(())
+    }
+
+    async fn events_between(&self, tenant_id: Uuid, since: DateTime<Utc>, until: DateTime<Utc>) -> AppResult<Vec<OutboxEvent>> {
+        let sql = format!(
+            r#"SELECT {} FROM outbox_events
+            WHERE tenant_id = $1 AND created_at >= $2 AND created_at < $3
+            ORDER BY created_at, entity_seq"#,
+            EVENT_COLUMNS
+        );
+        let events = sqlx::query_as::<_, OutboxEvent>(&sql)
+            .bind(tenant_id)
+            .bind(since)
+            .bind(until)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(events)
+    }
+
+    async fn due_deliveries(&self, now: DateTime<Utc>, limit: i64) -> AppResult<Vec<WebhookDelivery>> {
+        let columns: Vec<String> = DELIVERY_COLUMNS.split(", ").map(|c| format!("d.{}", c.trim())).collect();
+        let sql = format!(
+            r#"SELECT {} FROM webhook_deliveries d
+            JOIN webhook_endpoints e ON e.id = d.endpoint_id
+            WHERE d.status = 'pending' AND d.next_attempt_at <= $1
+              AND e.active AND (e.paused_until IS NULL OR e.paused_until <= $1)
+              AND NOT EXISTS (
+                SELECT 1 FROM webhook_deliveries p
+                WHERE p.endpoint_id = d.endpoint_id AND p.entity_id = d.entity_id
+                  AND p.status = 'pending' AND p.entity_seq < d.entity_seq
+              )
+            ORDER BY d.next_attempt_at, d.entity_seq
+            LIMIT $2"#,
+            columns.join(", ")
+        );
+        let deliveries = sqlx::query_as::<_, WebhookDelivery>(&sql)
+            .bind(now)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(deliveries)
+    }
+
+    async fn claim_delivery(&self, id: Uuid, seen: DateTime<Utc>, lease_until: DateTime<Utc>) -> AppResult<bool> {
+        let result = sqlx::query(
+            "UPDATE webhook_deliveries SET next_attempt_at = $1 WHERE id = $2 AND status = 'pending' AND next_attempt_at = $3"
+        )
+        .bind(lease_until)
+        .bind(id)
+        .bind(seen)
+        .execute(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn save_delivery(&self, delivery: &WebhookDelivery) -> AppResult<()> {
+        sqlx::query(
+            r#"UPDATE webhook_deliveries
+            SET status = $1, attempts = $2, next_attempt_at = $3, last_status_code = $4, last_error = $5, delivered_at = $6
+            WHERE id = $7"#
+        )
+        .bind(delivery.status)
+        .bind(delivery.attempts)
+        .bind(delivery.next_attempt_at)
+        .bind(delivery.last_status_code)
+        .bind(&delivery.last_error)
+        .bind(delivery.delivered_at)
+        .bind(delivery.id)
+        .execute(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(())
+    }
+
+    async fn list_deliveries(&self, tenant_id: Uuid, endpoint_id: Uuid, limit: i64) -> AppResult<Vec<WebhookDelivery>> {
+        let sql = format!(
+            "SELECT {} FROM webhook_deliveries WHERE tenant_id = $1 AND endpoint_id = $2 ORDER BY created_at DESC LIMIT $3",
+            DELIVERY_COLUMNS
+        );
+        let deliveries = sqlx::query_as::<_, WebhookDelivery>(&sql)
+            .bind(tenant_id)
+            .bind(endpoint_id)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(deliveries)
+    }
+
+    async fn requeue(&self, deliveries: &[WebhookDelivery]) -> AppResult<usize> {
+        let mut tx = self.pool.begin().await.map_err(AppError::Database)?;
+        let mut queued = 0;
+        for delivery in deliveries {
+            if upsert_delivery(&mut tx, delivery, true).await? {
+                queued += 1;
+            }
+        }
+        tx.commit().await.map_err(AppError::Database)?;
+
+        Ok(queued)
+    }
+}
+
+#[derive(Default)]
+struct InMemoryState {
+    endpoints: HashMap<Uuid, WebhookEndpoint>,
+    events: Vec<OutboxEvent>,
+    deliveries: HashMap<Uuid, WebhookDelivery>,
+}
+
+#[derive(Default)]
+pub struct InMemoryWebhookStore {
+    state: Mutex<InMemoryState>,
+}
+
+impl InMemoryWebhookStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl WebhookStore for InMemoryWebhookStore {
+    async fn create_endpoint(&self, endpoint: &WebhookEndpoint) -> AppResult<()> {
+        self.state.lock().await.endpoints.insert(endpoint.id, endpoint.clone());
+        Ok(())
+    }
+
+    async fn get_endpoint(&self, tenant_id: Uuid, id: Uuid) -> AppResult<Option<WebhookEndpoint>> {
+        Ok(self.state.lock().await.endpoints.get(&id).filter(|e| e.tenant_id == tenant_id).cloned())
+    }
+
+    async fn list_endpoints(&self, tenant_id: Uuid) -> AppResult<Vec<WebhookEndpoint>> {
+        let state = self.state.lock().await;
+        let mut endpoints: Vec<WebhookEndpoint> = state.endpoints.values().filter(|e| e.tenant_id == tenant_id).cloned().collect();
+        endpoints.sort_by_key(|e| e.created_at);
+        Ok(endpoints)
+    }
+
+    async fn update_endpoint(&self, endpoint: &WebhookEndpoint) -> AppResult<()> {
+        if let Some(existing) = self.state.lock().await.endpoints.get_mut(&endpoint.id).filter(|e| e.tenant_id == endpoint.tenant_id) {
+            existing.url = endpoint.url.clone();
+            existing.description = endpoint.description.clone();
+            existing.event_types = endpoint.event_types.clone();
+            existing.active = endpoint.active;
+            existing.updated_at = endpoint.updated_at;
+        }
+        Ok(())
+    }
+
+    async fn delete_endpoint(&self, tenant_id: Uuid, id: Uuid) -> AppResult<bool> {
+        let mut state = self.state.lock().await;
+        if state.endpoints.get(&id).is_none_or(|e| e.tenant_id != tenant_id) {
+            return Ok(false);
+        }
+        state.endpoints.remove(&id);
+        state.deliveries.retain(|_, d| d.endpoint_id != id);
+        Ok(true)
+    }
+
+    async fn set_circuit(&self, id: Uuid, consecutive_failures: i64, paused_until: Option<DateTime<Utc>>) -> AppResult<()> {
+        if let Some(endpoint) = self.state.lock().await.endpoints.get_mut(&id) {
+            endpoint.consecutive_failures = consecutive_failures;
+            endpoint.paused_until = paused_until;
+        }
+        Ok(())
+    }
+
+    async fn append(&self, event: &NewEvent) -> AppResult<OutboxEvent> {
+        let mut state = self.state.lock().await;
+        let entity_seq = state.events.iter().filter(|e| e.entity_id == event.entity_id).map(|e| e.entity_seq).max().unwrap_or(0) + 1;
+        let event = OutboxEvent {
+            id: Uuid::new_v4(),
+            tenant_id: event.tenant_id,
+            event_type: event.event_type.clone(),
+            entity_type: event.entity_type.clone(),
+            entity_id: event.entity_id,
+            entity_seq,
+            before: event.before.clone(),
+            after: event.after.clone(),
+            created_at: Utc::now(),
+            dispatched_at: None,
+        };
+        state.events.push(event.clone());
+        Ok(event)
+    }
+
+    async fn get_event(&self, id: Uuid) -> AppResult<Option<OutboxEvent>> {
+        Ok(self.state.lock().await.events.iter().find(|e| e.id == id).cloned())
+    }
+
+    async fn undispatched(&self, limit: i64) -> AppResult<Vec<OutboxEvent>> {
+        let state = self.state.lock().await;
+        Ok(state.events.iter().filter(|e| e.dispatched_at.is_none()).take(limit as usize).cloned().collect())
+    }
+
+    async fn fan_out(&self, event_id: Uuid, deliveries: &[WebhookDelivery], now: DateTime<Utc>) -> AppResult<()> {
+        let mut state = self.state.lock().await;
+        for delivery in deliveries {
+            let exists = state.deliveries.values().any(|d| d.event_id == delivery.event_id && d.endpoint_id == delivery.endpoint_id);
+            if !exists {
+                state.deliveries.insert(delivery.id, delivery.clone());
+            }
+        }
+        if let Some(event) = state.events.iter_mut().find(|e| e.id == event_id) {
+            event.dispatched_at.get_or_insert(now);
+        }
+        Ok(())
+    }
+
+    async fn events_between(&self, tenant_id: Uuid, since: DateTime<Utc>, until: DateTime<Utc>) -> AppResult<Vec<OutboxEvent>> {
+        let state = self.state.lock().await;
+        Ok(state
+            .events
+            .iter()
+            .filter(|e| e.tenant_id == tenant_id && e.created_at >= since && e.created_at < until)
+            .cloned()
+            .collect())
+    }
+
+    async fn due_deliveries(&self, now: DateTime<Utc>, limit: i64) -> AppResult<Vec<WebhookDelivery>> {
+        let state = self.state.lock().await;
+        let pending: Vec<&WebhookDelivery> = state.deliveries.values().filter(|d| d.status == DeliveryStatus::Pending).collect();
+        let mut due: Vec<WebhookDelivery> = pending
+            .iter()
+            .filter(|d| d.next_attempt_at <= now)
+            .filter(|d| {
+                state
+                    .endpoints
+                    .get(&d.endpoint_id)
+                    .is_some_and(|e| e.active && e.paused_until.is_none_or(|until| until <= now))
+            })
+            .filter(|d| {
+                !pending
+                    .iter()
+                    .any(|p| p.endpoint_id == d.endpoint_id && p.entity_id == d.entity_id && p.entity_seq < d.entity_seq)
+            })
+            .map(|d| (*d).clone())
+            .collect();
+        due.sort_by_key(|d| (d.next_attempt_at, d.entity_seq));
+        due.truncate(limit as usize);
+        Ok(due)
+    }
+
+    async fn claim_delivery(&self, id: Uuid, seen: DateTime<Utc>, lease_until: DateTime<Utc>) -> AppResult<bool> {
+        let mut state = self.state.lock().await;
+        match state.deliveries.get_mut(&id).filter(|d| d.status == DeliveryStatus::Pending && d.next_attempt_at == seen) {
+            Some(delivery) => {
+                delivery.next_attempt_at = lease_until;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn save_delivery(&self, delivery: &WebhookDelivery) -> AppResult<()> {
+        self.state.lock().await.deliveries.insert(delivery.id, delivery.clone());
+        Ok(())
+    }
+
+    async fn list_deliveries(&self, tenant_id: Uuid, endpoint_id: Uuid, limit: i64) -> AppResult<Vec<WebhookDelivery>> {
+        let state = self.state.lock().await;
+        let mut deliveries: Vec<WebhookDelivery> = state
+            .deliveries
+            .values()
+            .filter(|d| d.tenant_id == tenant_id && d.endpoint_id == endpoint_id)
+            .cloned()
+            .collect();
+        deliveries.sort_by_key(|d| std::cmp::Reverse(d.created_at));
+        deliveries.truncate(limit as usize);
+        Ok(deliveries)
+    }
+
+    async fn requeue(&self, deliveries: &[WebhookDelivery]) -> AppResult<usize> {
+        let mut state = self.state.lock().await;
+        for delivery in deliveries {
+            let existing = state
+                .deliveries
+                .values_mut()
+                .find(|d| d.event_id == delivery.event_id && d.endpoint_id == delivery.endpoint_id);
+            match existing {
+                Some(existing) => {
+                    existing.status = DeliveryStatus::Pending;
+                    existing.attempts = 0;
+                    existing.next_attempt_at = delivery.next_attempt_at;
+                    existing.last_status_code = None;
+                    existing.last_error = None;
+                    existing.delivered_at = None;
+                }
+                None => {
+                    state.deliveries.insert(delivery.id, delivery.clone());
+                }
+            }
+        }
+        Ok(deliveries.len())
+    }
+}
@@ -0,0 +1,551 @@
+use std::cmp::Ordering;
+use std::sync::Arc;
+
+use axum::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::error::{AppError, AppResult};
+
+pub mod store;
+
+pub use store::{InMemoryWorkflowRunStore, PgWorkflowRunStore};
+
+pub const DEFAULT_PAGE_SIZE: i64 = 50;
+pub const MAX_PAGE_SIZE: i64 = 500;
+pub const DEFAULT_FAILURE_WINDOW_DAYS: i64 = 7;
+const MAX_FAILURE_GROUPS: i64 = 200;
+// Failed tasks that didn't report an error code are grouped under this one
+pub const UNKNOWN_ERROR_CODE: &str = "UNKNOWN";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "VARCHAR", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    Pending,
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+    TimedOut,
+}
+
+impl RunStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RunStatus::Pending => "pending",
+            RunStatus::Running => "running",
+            RunStatus::Succeeded => "succeeded",
+            RunStatus::Failed => "failed",
+            RunStatus::Cancelled => "cancelled",
+            RunStatus::TimedOut => "timed_out",
+        }
+    }
+
+    // What failure analytics count
+    pub fn is_failure(&self) -> bool {
+        matches!(self, RunStatus::Failed | RunStatus::TimedOut)
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        !matches!(self, RunStatus::Pending | RunStatus::Running)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "VARCHAR", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum TriggerType {
+    Manual,
+    Schedule,
+    Cron,
+    Event,
+    Webhook,
+    Timer,
+    Queue,
+    Stream,
+}
+
+impl TriggerType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TriggerType::Manual => "manual",
+            TriggerType::Schedule => "schedule",
+            TriggerType::Cron => "cron",
+            TriggerType::Event => "event",
+            TriggerType::Webhook => "webhook",
+            TriggerType::Timer => "timer",
+            TriggerType::Queue => "queue",
+            TriggerType::Stream => "stream",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaskRunRecord {
+    pub task_id: String,
+    pub status: RunStatus,
+    pub started_at: DateTime<Utc>,
+    #[serde(default)]
+    pub finished_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub error_code: Option<String>,
+    #[serde(default)]
+    pub error_message: Option<String>,
+}
+
+// A run as reported by the executor, on each status change or once it finishes. Reporting the
+// same `run_id` again replaces the earlier report.
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewWorkflowRun {
+    pub run_id: String,
+    pub workflow_id: String,
+    pub status: RunStatus,
+    pub trigger_type: TriggerType,
+    pub started_at: DateTime<Utc>,
+    #[serde(default)]
+    pub finished_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub task_runs: Vec<TaskRunRecord>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WorkflowRunRecord {
+    pub id: Uuid,
+    pub project_id: Uuid,
+    pub run_id: String,
+    pub workflow_id: String,
+    pub status: RunStatus,
+    pub trigger_type: TriggerType,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    // Unset until the run finishes
+    pub duration_ms: Option<i64>,
+    // Tasks that failed or timed out, in the order they were reported
+    pub failed_tasks: Vec<String>,
+    // Only filled in when a single run is fetched; searches leave it empty
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub task_runs: Vec<TaskRunRecord>,
+}
+
+impl WorkflowRunRecord {
+    fn from_new(project_id: Uuid, run: NewWorkflowRun) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            project_id,
+            duration_ms: run.finished_at.map(|finished| (finished - run.started_at).num_milliseconds()),
+            failed_tasks: run
+                .task_runs
+                .iter()
+                .filter(|t| t.status.is_failure())
+                .map(|t| t.task_id.clone())
+                .collect(),
+            run_id: run.run_id,
+            workflow_id: run.workflow_id,
+            status: run.status,
+            trigger_type: run.trigger_type,
+            started_at: run.started_at,
+            finished_at: run.finished_at,
+            task_runs: run.task_runs,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunSort {
+    #[default]
+    StartedAt,
+    Duration,
+}
+
+fn newest_first() -> bool {
+    true
+}
+
+// Every set filter must match; list filters match any of their values. Time bounds are inclusive
+// of `started_after` and exclusive of `started_before`, duration bounds inclusive; runs still in
+// progress have no duration and never match a duration filter.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RunQuery {
+    #[serde(default)]
+    pub workflow_id: Option<String>,
+    #[serde(default)]
+    pub statuses: Vec<RunStatus>,
+    #[serde(default)]
+    pub trigger_types: Vec<TriggerType>,
+    #[serde(default)]
+    pub started_after: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub started_before: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub min_duration_ms: Option<i64>,
+    #[serde(default)]
+    pub max_duration_ms: Option<i64>,
+    // Runs in which this task failed or timed out
+    #[serde(default)]
+    pub failing_task: Option<String>,
+    #[serde(default)]
+    pub sort: RunSort,
+    #[serde(default = "newest_first")]
+    pub descending: bool,
+}
+
+impl RunQuery {
+    pub fn matches(&self, run: &WorkflowRunRecord) -> bool {
+        let duration = |bound: Option<i64>, within: fn(i64, i64) -> bool| {
+            bound.is_none_or(|bound| run.duration_ms.is_some_and(|d| within(d, bound)))
+        };
+        self.workflow_id.as_deref().is_none_or(|w| w == run.workflow_id)
+            && (self.statuses.is_empty() || self.statuses.contains(&run.status))
+            && (self.trigger_types.is_empty() || self.trigger_types.contains(&run.trigger_type))
+            && self.started_after.is_none_or(|after| run.started_at >= after)
+            && self.started_before.is_none_or(|before| run.started_at < before)
+            && duration(self.min_duration_ms, |d, min| d >= min)
+            && duration(self.max_duration_ms, |d, max| d <= max)
+            && self.failing_task.as_deref().is_none_or(|task| run.failed_tasks.iter().any(|t| t == task))
+    }
+
+    // Orders by the sort key, then id, so pages don't overlap when keys tie. Unfinished runs sort
+    // as the shortest when ordering by duration.
+    fn compare(&self, a: &WorkflowRunRecord, b: &WorkflowRunRecord) -> Ordering {
+        let order = match self.sort {
+            RunSort::StartedAt => a.started_at.cmp(&b.started_at),
+            RunSort::Duration => a.duration_ms.unwrap_or(-1).cmp(&b.duration_ms.unwrap_or(-1)),
+        }
+        .then_with(|| a.id.cmp(&b.id));
+        if self.descending {
+            order.reverse()
+        } else {
+            order
+        }
+    }
+
+    fn validate(&self) -> AppResult<()> {
+        if let (Some(after), Some(before)) = (self.started_after, self.started_before) {
+            if after >= before {
+                return Err(AppError::Validation("started_after must be before started_before".into()));
+            }
+        }
+        if self.min_duration_ms.is_some_and(|d| d < 0) || self.max_duration_ms.is_some_and(|d| d < 0) {
+            return Err(AppError::Validation("Durations must not be negative".into()));
+        }
+        if let (Some(min), Some(max)) = (self.min_duration_ms, self.max_duration_ms) {
+            if min > max {
+                return Err(AppError::Validation("min_duration_ms must not exceed max_duration_ms".into()));
+            }
+        }
+        if self.failing_task.as_deref().is_some_and(|t| t.trim().is_empty()) {
+            return Err(AppError::Validation("failing_task must not be blank".into()));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RunPage {
+    pub runs: Vec<WorkflowRunRecord>,
+    // Pass back as `after` for the next page; absent on the last page
+    pub next_cursor: Option<Uuid>,
+}
+
+// Failed task runs of one task with one error code, e.g. deploy-app failing with HTTP_502
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, sqlx::FromRow)]
+pub struct FailureGroup {
+    pub workflow_id: String,
+    pub task_id: String,
+    pub error_code: String,
+    pub failures: i64,
+    pub first_failed_at: DateTime<Utc>,
+    pub last_failed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FailureReport {
+    pub since: DateTime<Utc>,
+    pub until: DateTime<Utc>,
+    pub total_failures: i64,
+    // Most frequent first
+    pub groups: Vec<FailureGroup>,
+}
+
+#[async_trait]
+pub trait WorkflowRunStore: Send + Sync {
+    // Inserts or replaces the run with the same project and `run_id`, keeping its id
+    async fn upsert_run(&self, run: &WorkflowRunRecord) -> AppResult<WorkflowRunRecord>;
+    async fn get_run(&self, project_id: Uuid, id: Uuid) -> AppResult<Option<WorkflowRunRecord>>;
+    async fn search(&self, project_id: Uuid, query: &RunQuery, after: Option<Uuid>, limit: i64) -> AppResult<Vec<WorkflowRunRecord>>;
+    // Task runs that failed or timed out and started in [since, until), grouped by workflow, task
+    // and error code, most frequent first
+    async fn failures(
+        &self,
+        project_id: Uuid,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+        workflow_id: Option<&str>,
+        limit: i64,
+    ) -> AppResult<Vec<FailureGroup>>;
+}
+
+// Run history across every workflow of a project, as reported by the executors
+pub struct WorkflowRunService {
+    store: Arc<dyn WorkflowRunStore>,
+}
+
+impl WorkflowRunService {
+    pub fn new(store: Arc<dyn WorkflowRunStore>) -> Self {
+        Self { store }
+    }
+
+    pub async fn record(&self, project_id: Uuid, run: NewWorkflowRun) -> AppResult<WorkflowRunRecord> {
+        if run.run_id.trim().is_empty() || run.workflow_id.trim().is_empty() {
+            return Err(AppError::Validation("Runs need a run_id and a workflow_id".into()));
+        }
+        if run.finished_at.is_some_and(|finished| finished < run.started_at) {
+            return Err(AppError::Validation("A run can't finish before it started".into()));
+        }
+        if run.status.is_terminal() != run.finished_at.is_some() {
+            return Err(AppError::Validation("finished_at must be set exactly when the run has finished".into()));
+        }
+        if let Some(task) = run.task_runs.iter().find(|t| t.finished_at.is_some_and(|f| f < t.started_at)) {
+            return Err(AppError::Validation(format!("Task {} can't finish before it started", task.task_id)));
+        }
+        self.store.upsert_run(&WorkflowRunRecord::from_new(project_id, run)).await
+    }
+
+    pub async fn run(&self, project_id: Uuid, id: Uuid) -> AppResult<WorkflowRunRecord> {
+        self.store
+            .get_run(project_id, id)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("Workflow run {} not found", id)))
+    }
+
+    pub async fn search(&self, project_id: Uuid, query: &RunQuery, after: Option<Uuid>, limit: i64) -> AppResult<RunPage> {
+        query.validate()?;
+        let limit = limit.clamp(1, MAX_PAGE_SIZE);
+        let mut runs = self.store.search(project_id, query, after, limit + 1).await?;
+        let next_cursor = if runs.len() as i64 > limit {
+            runs.truncate(limit as usize);
+            runs.last().map(|r| r.id)
+        } else {
+            None
+        };
+        Ok(RunPage { runs, next_cursor })
+    }
+
+    // Defaults to the week up to `now`
+    pub async fn failures(
+        &self,
+        project_id: Uuid,
+        since: Option<DateTime<Utc>>,
+        until: Option<DateTime<Utc>>,
+        workflow_id: Option<&str>,
+        now: DateTime<Utc>,
+    ) -> AppResult<FailureReport> {
+        let until = until.unwrap_or(now);
+        let since = since.unwrap_or(until - Duration::days(DEFAULT_FAILURE_WINDOW_DAYS));
+        if since >= until {
+            return Err(AppError::Validation("since must be before until".into()));
+        }
+        let groups = self.store.failures(project_id, since, until, workflow_id, MAX_FAILURE_GROUPS).await?;
+        Ok(FailureReport { since, until, total_failures: groups.iter().map(|g| g.failures).sum(), groups })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashSet};
+
+    use super::*;
+    use chrono::TimeZone;
+
+    const RUNS: usize = 20_000;
+    const WORKFLOWS: [&str; 4] = ["build", "release", "nightly-backup", "cleanup"];
+    const TRIGGERS: [TriggerType; 4] = [TriggerType::Manual, TriggerType::Cron, TriggerType::Webhook, TriggerType::Event];
+
+    // Deterministic so failures can be reproduced from the seed
+    struct Lcg(u64);
+
+    impl Lcg {
+        fn next(&mut self, bound: u64) -> u64 {
+            self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (self.0 >> 33) % bound
+        }
+    }
+
+    // A year of history: roughly one run every 25 minutes across four workflows. One in eight
+    // fails in deploy-app with HTTP_502 or a timeout, one in twenty fails in test without a code.
+    async fn seeded() -> (WorkflowRunService, Uuid, Vec<WorkflowRunRecord>, DateTime<Utc>) {
+        let store = Arc::new(InMemoryWorkflowRunStore::new());
+        let service = WorkflowRunService::new(store);
+        let project = Uuid::new_v4();
+        let origin = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+        let mut rng = Lcg(42);
+        let mut recorded = Vec::with_capacity(RUNS);
+        for i in 0..RUNS {
+            let started_at = origin + Duration::minutes(i as i64 * 25) + Duration::seconds(rng.next(600) as i64);
+            let duration = Duration::milliseconds(5_000 + rng.next(3_600_000) as i64);
+            let roll = rng.next(40);
+            let (status, failing, code) = match roll {
+                0..=4 => (RunStatus::Failed, Some("deploy-app"), Some("HTTP_502")),
+                5 => (RunStatus::TimedOut, Some("deploy-app"), Some("TIMEOUT")),
+                6 | 7 => (RunStatus::Failed, Some("test"), None),
+                8 if i % 1000 == 0 => (RunStatus::Running, None, None),
+                _ => (RunStatus::Succeeded, None, None),
+            };
+            let finished_at = status.is_terminal().then_some(started_at + duration);
+            let task_runs = ["checkout", "test", "deploy-app"]
+                .iter()
+                .map(|&task_id| TaskRunRecord {
+                    task_id: task_id.into(),
+                    status: match failing {
+                        Some(f) if f == task_id => status,
+                        _ => RunStatus::Succeeded,
+                    },
+                    started_at,
+                    finished_at: Some(started_at + Duration::seconds(1)),
+                    error_code: if failing == Some(task_id) { code.map(String::from) } else { None },
+                    error_message: None,
+                })
+                .collect();
+            let run = NewWorkflowRun {
+                run_id: format!("run-{}", i),
+                workflow_id: WORKFLOWS[i % WORKFLOWS.len()].into(),
+                status,
+                trigger_type: TRIGGERS[rng.next(TRIGGERS.len() as u64) as usize],
+                started_at,
+                finished_at,
+                task_runs,
+            };
+            recorded.push(service.record(project, run).await.unwrap());
+        }
+        (service, project, recorded, origin)
+    }
+
+    // Walks every page so pagination is checked along with the filters
+    async fn search_all(service: &WorkflowRunService, project: Uuid, query: &RunQuery) -> Vec<WorkflowRunRecord> {
+        let mut runs = Vec::new();
+        let mut after = None;
+        loop {
+            let page = service.search(project, query, after, 337).await.unwrap();
+            runs.extend(page.runs);
+            match page.next_cursor {
+                Some(cursor) => after = Some(cursor),
+                None => return runs,
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_search_filters_over_large_history() {
+        let (service, project, recorded, origin) = seeded().await;
+        let queries = [
+            RunQuery { statuses: vec![RunStatus::Failed, RunStatus::TimedOut], ..Default::default() },
+            RunQuery {
+                workflow_id: Some("release".into()),
+                trigger_types: vec![TriggerType::Webhook],
+                started_after: Some(origin + Duration::days(30)),
+                started_before: Some(origin + Duration::days(120)),
+                ..Default::default()
+            },
+            RunQuery {
+                min_duration_ms: Some(600_000),
+                max_duration_ms: Some(900_000),
+                sort: RunSort::Duration,
+                descending: false,
+                ..Default::default()
+            },
+            RunQuery { failing_task: Some("deploy-app".into()), statuses: vec![RunStatus::TimedOut], ..Default::default() },
+            RunQuery { sort: RunSort::Duration, ..Default::default() },
+        ];
+        for query in &queries {
+            let found = search_all(&service, project, query).await;
+            let expected: HashSet<Uuid> = recorded.iter().filter(|r| query.matches(r)).map(|r| r.id).collect();
+            assert!(!expected.is_empty(), "{:?} matches nothing", query);
+            assert_eq!(found.len(), expected.len(), "{:?}", query);
+            assert!(found.iter().all(|r| expected.contains(&r.id)), "{:?}", query);
+            assert!(found.windows(2).all(|w| query.compare(&w[0], &w[1]).is_lt()), "{:?} out of order", query);
+        }
+
+        // Spot-check the filters against the records directly rather than through `matches`
+        let timed_out = RunQuery { failing_task: Some("deploy-app".into()), statuses: vec![RunStatus::TimedOut], ..Default::default() };
+        let found = search_all(&service, project, &timed_out).await;
+        assert!(found.iter().all(|r| r.status == RunStatus::TimedOut && r.failed_tasks == ["deploy-app"]));
+        let by_duration = search_all(&service, project, &queries[2]).await;
+        assert!(by_duration.iter().all(|r| (600_000..=900_000).contains(&r.duration_ms.unwrap())));
+        let newest = service.search(project, &RunQuery::default(), None, 1).await.unwrap();
+        assert_eq!(newest.runs[0].run_id, format!("run-{}", RUNS - 1));
+
+        let invalid = RunQuery { min_duration_ms: Some(10), max_duration_ms: Some(5), ..Default::default() };
+        assert!(matches!(service.search(project, &invalid, None, 10).await, Err(AppError::Validation(_))));
+        // Other projects see nothing
+        assert!(service.search(Uuid::new_v4(), &RunQuery::default(), None, 10).await.unwrap().runs.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_failure_analytics() {
+        let (service, project, recorded, origin) = seeded().await;
+        let since = origin + Duration::days(200);
+        let until = since + Duration::days(7);
+
+        let mut expected: BTreeMap<(String, String, String), i64> = BTreeMap::new();
+        for run in recorded.iter().filter(|r| r.started_at >= since && r.started_at < until) {
+            for task in &run.failed_tasks {
+                let code = match (task.as_str(), run.status) {
+                    ("deploy-app", RunStatus::TimedOut) => "TIMEOUT",
+                    ("deploy-app", _) => "HTTP_502",
+                    _ => UNKNOWN_ERROR_CODE,
+                };
+                *expected.entry((run.workflow_id.clone(), task.clone(), code.into())).or_default() += 1;
+            }
+        }
+
+        let report = service.failures(project, Some(since), Some(until), None, Utc::now()).await.unwrap();
+        let found: BTreeMap<_, _> = report
+            .groups
+            .iter()
+            .map(|g| ((g.workflow_id.clone(), g.task_id.clone(), g.error_code.clone()), g.failures))
+            .collect();
+        assert_eq!(found, expected);
+        assert_eq!(report.total_failures, expected.values().sum::<i64>());
+        assert!(report.groups.windows(2).all(|w| w[0].failures >= w[1].failures));
+        // HTTP_502 in deploy-app is seeded as the most common failure
+        assert_eq!((report.groups[0].task_id.as_str(), report.groups[0].error_code.as_str()), ("deploy-app", "HTTP_502"));
+        assert!(report.groups.iter().all(|g| g.first_failed_at >= since && g.last_failed_at < until));
+
+        let release = service.failures(project, Some(since), Some(until), Some("release"), Utc::now()).await.unwrap();
+        assert!(release.groups.iter().all(|g| g.workflow_id == "release"));
+        assert_eq!(
+            release.total_failures,
+            expected.iter().filter(|((w, _, _), _)| w == "release").map(|(_, n)| n).sum::<i64>()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reporting_a_run_again_replaces_it() {
+        let service = WorkflowRunService::new(Arc::new(InMemoryWorkflowRunStore::new()));
+        let project = Uuid::new_v4();
+        let started_at = Utc::now() - Duration::minutes(5);
+        let mut run = NewWorkflowRun {
+            run_id: "run-1".into(),
+            workflow_id: "build".into(),
+            status: RunStatus::Running,
+            trigger_type: TriggerType::Manual,
+            started_at,
+            finished_at: None,
+            task_runs: Vec::new(),
+        };
+        let first = service.record(project, run.clone()).await.unwrap();
+        assert_eq!(first.duration_ms, None);
+
+        run.status = RunStatus::Succeeded;
+        run.finished_at = Some(started_at + Duration::seconds(90));
+        let second = service.record(project, run.clone()).await.unwrap();
+        assert_eq!((second.id, second.duration_ms), (first.id, Some(90_000)));
+        assert_eq!(service.run(project, first.id).await.unwrap().status, RunStatus::Succeeded);
+
+        run.finished_at = None;
+        assert!(matches!(service.record(project, run).await, Err(AppError::Validation(_))));
+    }
+}
@@ -0,0 +1,375 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, QueryBuilder}; // CockroachDB uses PostgreSQL protocol
+use uuid::Uuid;
+
+use super::{
+    FailureGroup, RunQuery, RunSort, RunStatus, TaskRunRecord, TriggerType, WorkflowRunRecord, WorkflowRunStore,
+    UNKNOWN_ERROR_CODE,
+};
+use crate::error::{AppError, AppResult};
+
+const RUN_COLUMNS: &str = "id, project_id, run_id, workflow_id, status, trigger_type, started_at, finished_at, \
+    duration_ms, failed_tasks";
+
+#[derive(sqlx::FromRow)]
+struct RunRow {
+    id: Uuid,
+    project_id: Uuid,
+    run_id: String,
+    workflow_id: String,
+    status: RunStatus,
+    trigger_type: TriggerType,
+    started_at: DateTime<Utc>,
+    finished_at: Option<DateTime<Utc>>,
+    duration_ms: Option<i64>,
+    failed_tasks: Vec<String>,
+}
+
+impl From<RunRow> for WorkflowRunRecord {
+    fn from(row: RunRow) -> Self {
+        Self {
+            id: row.id,
+            project_id: row.project_id,
+            run_id: row.run_id,
+            workflow_id: row.workflow_id,
+            status: row.status,
+            trigger_type: row.trigger_type,
+            started_at: row.started_at,
+            finished_at: row.finished_at,
+            duration_ms: row.duration_ms,
+            failed_tasks: row.failed_tasks,
+            task_runs: Vec::new(),
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct TaskRunRow {
+    task_id: String,
+    status: RunStatus,
+    started_at: DateTime<Utc>,
+    finished_at: Option<DateTime<Utc>>,
+    error_code: Option<String>,
+    error_message: Option<String>,
+}
+
+impl From<TaskRunRow> for TaskRunRecord {
+    fn from(row: TaskRunRow) -> Self {
+        Self {
+            task_id: row.task_id,
+            status: row.status,
+            started_at: row.started_at,
+            finished_at: row.finished_at,
+            error_code: row.error_code,
+            error_message: row.error_message,
+        }
+    }
+}
+
+fn sort_column(sort: RunSort) -> &'static str {
+    match sort {
+        RunSort::StartedAt => "started_at",
+        RunSort::Duration => "COALESCE(duration_ms, -1)",
+    }
+}
+
+// The WHERE clause for a search; every filter is served by a (project_id, ...) index
+fn push_filters<'a>(builder: &mut QueryBuilder<'a, Postgres>, project_id: Uuid, query: &'a RunQuery) {
+    builder.push(" WHERE project_id = ").push_bind(project_id);
+    if let Some(workflow_id) = &query.workflow_id {
+        builder.push(" AND workflow_id = ").push_bind(workflow_id);
+    }
+    if !query.statuses.is_empty() {
+        let statuses: Vec<&str> = query.statuses.iter().map(RunStatus::as_str).collect();
+        builder.push(" AND status = ANY(").push_bind(statuses).push(")");
+    }
+    if !query.trigger_types.is_empty() {
+        let triggers: Vec<&str> = query.trigger_types.iter().map(TriggerType::as_str).collect();
+        builder.push(" AND trigger_type = ANY(").push_bind(triggers).push(")");
+    }
+    if let Some(after) = query.started_after {
+        builder.push(" AND started_at >= ").push_bind(after);
+    }
+    if let Some(before) = query.started_before {
+        builder.push(" AND started_at < ").push_bind(before);
+    }
+    if let Some(min) = query.min_duration_ms {
+        builder.push(" AND duration_ms >= ").push_bind(min);
+    }
+    if let Some(max) = query.max_duration_ms {
+        builder.push(" AND duration_ms <= ").push_bind(max);
+    }
+    if let Some(task) = &query.failing_task {
+        builder.push(" AND failed_tasks @> ARRAY[").push_bind(task).push("]");
+    }
+}
+
+fn search_query<'a>(project_id: Uuid, query: &'a RunQuery, after: Option<Uuid>, limit: i64) -> QueryBuilder<'a, Postgres> {
+    let sort = sort_column(query.sort);
+    let (direction, comparison) = if query.descending { ("DESC", "<") } else { ("ASC", ">") };
+    let mut builder = QueryBuilder::new(format!("SELECT {} FROM workflow_runs", RUN_COLUMNS));
+    push_filters(&mut builder, project_id, query);
+    if let Some(after) = after {
+        builder
+            .push(format!(" AND ({}, id) {} (SELECT {}, id FROM workflow_runs WHERE project_id = ", sort, comparison, sort))
+            .push_bind(project_id)
+            .push(" AND id = ")
+            .push_bind(after)
+            .push(")");
+    }
+    builder
+        .push(format!(" ORDER BY {} {}, id {} LIMIT ", sort, direction, direction))
+        .push_bind(limit);
+    builder
+}
+
+pub struct PgWorkflowRunStore {
+    pool: PgPool,
+}
+
+impl PgWorkflowRunStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl WorkflowRunStore for PgWorkflowRunStore {
+    async fn upsert_run(&self, run: &WorkflowRunRecord) -> AppResult<WorkflowRunRecord> {
+        let mut tx = self.pool.begin().await.map_err(AppError::Database)?;
+        let row = sqlx::query_as::<_, RunRow>(&format!(
+            r#"INSERT INTO workflow_runs ({})
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            ON CONFLICT (project_id, run_id) DO UPDATE SET
+            workflow_id = excluded.workflow_id, status = excluded.status, trigger_type = excluded.trigger_type,
+            started_at = excluded.started_at, finished_at = excluded.finished_at,
+            duration_ms = excluded.duration_ms, failed_tasks = excluded.failed_tasks
+            RETURNING {}"#,
+            RUN_COLUMNS, RUN_COLUMNS
+        ))
+        .bind(run.id)
+        .bind(run.project_id)
+        .bind(&run.run_id)
+        .bind(&run.workflow_id)
+        .bind(run.status)
+        .bind(run.trigger_type)
+        .bind(run.started_at)
+        .bind(run.finished_at)
+        .bind(run.duration_ms)
+        .bind(&run.failed_tasks)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(AppError::Database)?;
+
+        sqlx::query("DELETE FROM workflow_task_runs WHERE run_id = $1")
+            .bind(row.id)
+            .execute(&mut *tx)
+            .await
+            .map_err(AppError::Database)?;
+        if !run.task_runs.is_empty() {
+            let mut insert = QueryBuilder::new(
+                "INSERT INTO workflow_task_runs (run_id, task_id, project_id, workflow_id, status, started_at, \
+                finished_at, error_code, error_message) ",
+            );
+            insert.push_values(&run.task_runs, |mut values, task| {
+                values
+                    .push_bind(row.id)
+                    .push_bind(&task.task_id)
+                    .push_bind(run.project_id)
+                    .push_bind(&run.workflow_id)
+                    .push_bind(task.status)
+                    .push_bind(task.started_at)
+                    .push_bind(task.finished_at)
+                    .push_bind(&task.error_code)
+                    .push_bind(&task.error_message);
+            });
+            insert.build().execute(&mut *tx).await.map_err(AppError::Database)?;
+        }
+        tx.commit().await.map_err(AppError::Database)?;
+
+        Ok(WorkflowRunRecord { task_runs: run.task_runs.clone(), ..row.into() })
+    }
+
+    async fn get_run(&self, project_id: Uuid, id: Uuid) -> AppResult<Option<WorkflowRunRecord>> {
+        let Some(row) = sqlx::query_as::<_, RunRow>(&format!(
+            "SELECT {} FROM workflow_runs WHERE project_id = $1 AND id = $2",
+            RUN_COLUMNS
+        ))
+        .bind(project_id)
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(AppError::Database)?
+        else {
+            return Ok(None);
+        };
+
+        let task_runs = sqlx::query_as::<_, TaskRunRow>(
+            r#"SELECT task_id, status, started_at, finished_at, error_code, error_message
+            FROM workflow_task_runs WHERE run_id = $1 ORDER BY started_at, task_id"#
+        )
+        .bind(id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(Some(WorkflowRunRecord {
+            task_runs: task_runs.into_iter().map(TaskRunRecord::from).collect(),
+            ..row.into()
+        }))
+    }
+
+    async fn search(&self, project_id: Uuid, query: &RunQuery, after: Option<Uuid>, limit: i64) -> AppResult<Vec<WorkflowRunRecord>> {
+        let rows = search_query(project_id, query, after, limit)
+            .build_query_as::<RunRow>()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(rows.into_iter().map(WorkflowRunRecord::from).collect())
+    }
+
+    async fn failures(
+        &self,
+        project_id: Uuid,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+        workflow_id: Option<&str>,
+        limit: i64,
+    ) -> AppResult<Vec<FailureGroup>> {
+        // The status predicate matches the partial index exactly so the planner can use it
+        let groups = sqlx::query_as::<_, FailureGroup>(
+            r#"SELECT workflow_id, task_id, COALESCE(error_code, $5) AS error_code, COUNT(*) AS failures,
+                MIN(started_at) AS first_failed_at, MAX(started_at) AS last_failed_at
+            FROM workflow_task_runs
+            WHERE project_id = $1 AND status IN ('failed', 'timed_out')
+              AND started_at >= $2 AND started_at < $3 AND ($4::STRING IS NULL OR workflow_id = $4)
+            GROUP BY workflow_id, task_id, COALESCE(error_code, $5)
+            ORDER BY failures DESC, workflow_id, task_id, error_code
+            LIMIT $6"#
+        )
+        .bind(project_id)
+        .bind(since)
+        .bind(until)
+        .bind(workflow_id)
+        .bind(UNKNOWN_ERROR_CODE)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(groups)
+    }
+}
+
+#[derive(Default)]
+struct RunState {
+    // Keyed by project and the executor's run id
+    runs: HashMap<(Uuid, String), WorkflowRunRecord>,
+    ids: HashMap<Uuid, (Uuid, String)>,
+}
+
+#[derive(Default)]
+pub struct InMemoryWorkflowRunStore {
+    state: Mutex<RunState>,
+}
+
+impl InMemoryWorkflowRunStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl WorkflowRunStore for InMemoryWorkflowRunStore {
+    async fn upsert_run(&self, run: &WorkflowRunRecord) -> AppResult<WorkflowRunRecord> {
+        let mut state = self.state.lock().unwrap();
+        let key = (run.project_id, run.run_id.clone());
+        let mut saved = run.clone();
+        if let Some(existing) = state.runs.get(&key) {
+            saved.id = existing.id;
+        }
+        state.ids.insert(saved.id, key.clone());
+        state.runs.insert(key, saved.clone());
+        Ok(saved)
+    }
+
+    async fn get_run(&self, project_id: Uuid, id: Uuid) -> AppResult<Option<WorkflowRunRecord>> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .ids
+            .get(&id)
+            .filter(|(project, _)| *project == project_id)
+            .and_then(|key| state.runs.get(key))
+            .cloned())
+    }
+
+    async fn search(&self, project_id: Uuid, query: &RunQuery, after: Option<Uuid>, limit: i64) -> AppResult<Vec<WorkflowRunRecord>> {
+        let state = self.state.lock().unwrap();
+        let mut matches: Vec<&WorkflowRunRecord> = state
+            .runs
+            .values()
+            .filter(|r| r.project_id == project_id && query.matches(r))
+            .collect();
+        matches.sort_by(|a, b| query.compare(a, b));
+        let start = match after {
+            Some(after) => match state.runs.values().find(|r| r.id == after && r.project_id == project_id) {
+                Some(cursor) => matches.partition_point(|r| query.compare(r, cursor).is_le()),
+                None => return Ok(Vec::new()),
+            },
+            None => 0,
+        };
+        Ok(matches
+            .into_iter()
+            .skip(start)
+            .take(limit.max(0) as usize)
+            .map(|r| WorkflowRunRecord { task_runs: Vec::new(), ..r.clone() })
+            .collect())
+    }
+
+    async fn failures(
+        &self,
+        project_id: Uuid,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+        workflow_id: Option<&str>,
+        limit: i64,
+    ) -> AppResult<Vec<FailureGroup>> {
+        let state = self.state.lock().unwrap();
+        let mut groups: BTreeMap<(&str, &str, &str), FailureGroup> = BTreeMap::new();
+        let runs = state
+            .runs
+            .values()
+            .filter(|r| r.project_id == project_id && workflow_id.is_none_or(|w| w == r.workflow_id));
+        for run in runs {
+            for task in &run.task_runs {
+                if !task.status.is_failure() || task.started_at < since || task.started_at >= until {
+                    continue;
+                }
+                let code = task.error_code.as_deref().unwrap_or(UNKNOWN_ERROR_CODE);
+                let group = groups
+                    .entry((&run.workflow_id, &task.task_id, code))
+                    .or_insert_with(|| FailureGroup {
+                        workflow_id: run.workflow_id.clone(),
+                        task_id: task.task_id.clone(),
+                        error_code: code.to_string(),
+                        failures: 0,
+                        first_failed_at: task.started_at,
+                        last_failed_at: task.started_at,
+                    });
+                group.failures += 1;
+                group.first_failed_at = group.first_failed_at.min(task.started_at);
+                group.last_failed_at = group.last_failed_at.max(task.started_at);
+            }
+        }
+        // Stable, so ties keep the key order the Pg store uses
+        let mut groups: Vec<FailureGroup> = groups.into_values().collect();
+        groups.sort_by(|a, b| b.failures.cmp(&a.failures));
+        groups.truncate(limit.max(0) as usize);
+        Ok(groups)
+    }
+}